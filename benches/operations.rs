@@ -239,7 +239,7 @@ fn bench_package_operations(c: &mut Criterion) {
 
                 // Mount filesystems
                 if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-                    for (mount, device) in mountpoints.iter().rev() {
+                    for (mount, device) in &mountpoints {
                         g.mount_ro(device, mount).ok();
                     }
                 }
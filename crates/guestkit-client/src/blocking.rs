@@ -0,0 +1,115 @@
+//! Blocking client for callers without an async runtime
+//!
+//! Each client owns a current-thread Tokio runtime and drives the async
+//! [`WorkerClient`](crate::WorkerClient) on it. Do not use it from within
+//! an async context.
+
+use guestkit::core::retry::RetryConfig;
+use guestkit_job_spec::{JobDocument, ProtocolVersion};
+
+use crate::error::ClientResult;
+use crate::events::JobEventStream;
+use crate::types::{
+    CapabilitiesResponse, HealthResponse, JobCancelResponse, JobControlResponse, JobEvent,
+    JobListResponse, JobStatusResponse, JobSubmitResponse,
+};
+
+/// Blocking HTTP client for worker REST API
+pub struct WorkerClient {
+    inner: crate::WorkerClient,
+    runtime: tokio::runtime::Runtime,
+}
+
+impl WorkerClient {
+    /// Create a new client
+    pub fn new(base_url: impl Into<String>) -> ClientResult<Self> {
+        Self::from_async(crate::WorkerClient::new(base_url))
+    }
+
+    /// Wrap a configured async client
+    pub fn from_async(inner: crate::WorkerClient) -> ClientResult<Self> {
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_all()
+            .build()?;
+        Ok(Self { inner, runtime })
+    }
+
+    /// Use a specific retry policy (`max_attempts: 1` disables retries)
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.inner = self.inner.with_retry(retry);
+        self
+    }
+
+    /// Submit a job, in the newest protocol version the worker accepts
+    pub fn submit_job(&self, job: JobDocument) -> ClientResult<JobSubmitResponse> {
+        self.runtime.block_on(self.inner.submit_job(job))
+    }
+
+    /// Get job status
+    pub fn get_job_status(&self, job_id: &str) -> ClientResult<JobStatusResponse> {
+        self.runtime.block_on(self.inner.get_job_status(job_id))
+    }
+
+    /// Cancel a job
+    pub fn cancel_job(&self, job_id: &str) -> ClientResult<JobCancelResponse> {
+        self.runtime.block_on(self.inner.cancel_job(job_id))
+    }
+
+    /// Pause a running job at its next checkpoint
+    pub fn pause_job(&self, job_id: &str) -> ClientResult<JobControlResponse> {
+        self.runtime.block_on(self.inner.pause_job(job_id))
+    }
+
+    /// Resume a paused job
+    pub fn resume_job(&self, job_id: &str) -> ClientResult<JobControlResponse> {
+        self.runtime.block_on(self.inner.resume_job(job_id))
+    }
+
+    /// Get job result
+    pub fn get_job_result(&self, job_id: &str) -> ClientResult<serde_json::Value> {
+        self.runtime.block_on(self.inner.get_job_result(job_id))
+    }
+
+    /// List all jobs
+    pub fn list_jobs(&self) -> ClientResult<JobListResponse> {
+        self.runtime.block_on(self.inner.list_jobs())
+    }
+
+    /// Get worker capabilities
+    pub fn get_capabilities(&self) -> ClientResult<CapabilitiesResponse> {
+        self.runtime.block_on(self.inner.get_capabilities())
+    }
+
+    /// Health check
+    pub fn health_check(&self) -> ClientResult<HealthResponse> {
+        self.runtime.block_on(self.inner.health_check())
+    }
+
+    /// Pick the job protocol version to use with the worker
+    pub fn negotiate_protocol(&self) -> ClientResult<ProtocolVersion> {
+        self.runtime.block_on(self.inner.negotiate_protocol())
+    }
+
+    /// Stream a job's status, progress and state changes
+    pub fn stream_events(&self, job_id: &str) -> ClientResult<JobEvents<'_>> {
+        let stream = self.runtime.block_on(self.inner.stream_events(job_id))?;
+        Ok(JobEvents {
+            stream,
+            runtime: &self.runtime,
+        })
+    }
+}
+
+/// Iterator over the events of one job
+pub struct JobEvents<'a> {
+    stream: JobEventStream,
+    runtime: &'a tokio::runtime::Runtime,
+}
+
+impl Iterator for JobEvents<'_> {
+    type Item = ClientResult<JobEvent>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        self.runtime.block_on(self.stream.next())
+    }
+}
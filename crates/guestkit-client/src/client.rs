@@ -0,0 +1,292 @@
+//! Async client for the worker REST API
+
+use std::future::Future;
+
+use guestkit::core::retry::{retry_with_backoff, RetryConfig};
+use guestkit_job_spec::{negotiate, JobDocument, ProtocolVersion};
+use reqwest::Method;
+use serde::de::DeserializeOwned;
+
+use crate::error::{ClientError, ClientResult};
+use crate::events::JobEventStream;
+use crate::types::{
+    ApiErrorBody, ApiResponse, CapabilitiesResponse, HealthResponse, JobCancelResponse,
+    JobControlResponse, JobListResponse, JobStatusResponse, JobSubmitRequest, JobSubmitResponse,
+};
+
+/// HTTP client for worker REST API
+///
+/// Requests that fail with a transient error (see
+/// [`ClientError::is_retryable`]) are retried with exponential backoff.
+#[derive(Debug, Clone)]
+pub struct WorkerClient {
+    base_url: String,
+    client: reqwest::Client,
+    retry: RetryConfig,
+}
+
+impl WorkerClient {
+    /// Create a new client
+    pub fn new(base_url: impl Into<String>) -> Self {
+        Self {
+            base_url: base_url.into().trim_end_matches('/').to_string(),
+            client: reqwest::Client::new(),
+            retry: RetryConfig::default(),
+        }
+    }
+
+    /// Use a specific retry policy (`max_attempts: 1` disables retries)
+    pub fn with_retry(mut self, retry: RetryConfig) -> Self {
+        self.retry = retry;
+        self
+    }
+
+    /// Use a preconfigured HTTP client (timeouts, TLS, proxies)
+    pub fn with_http_client(mut self, client: reqwest::Client) -> Self {
+        self.client = client;
+        self
+    }
+
+    /// Worker base URL
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Submit a job, in the newest protocol version the worker accepts
+    pub async fn submit_job(&self, job: JobDocument) -> ClientResult<JobSubmitResponse> {
+        let version = self.negotiate_protocol().await?;
+        let job = job.to_version(version)?;
+        let body = serde_json::to_value(JobSubmitRequest { job })?;
+
+        self.request(Method::POST, "/api/v1/jobs", Some(&body))
+            .await
+    }
+
+    /// Get job status
+    pub async fn get_job_status(&self, job_id: &str) -> ClientResult<JobStatusResponse> {
+        self.request(Method::GET, &format!("/api/v1/jobs/{}", job_id), None)
+            .await
+    }
+
+    /// Cancel a job
+    pub async fn cancel_job(&self, job_id: &str) -> ClientResult<JobCancelResponse> {
+        self.request(Method::DELETE, &format!("/api/v1/jobs/{}", job_id), None)
+            .await
+    }
+
+    /// Pause a running job at its next checkpoint
+    pub async fn pause_job(&self, job_id: &str) -> ClientResult<JobControlResponse> {
+        self.request(Method::POST, &format!("/api/v1/jobs/{}/pause", job_id), None)
+            .await
+    }
+
+    /// Resume a paused job
+    pub async fn resume_job(&self, job_id: &str) -> ClientResult<JobControlResponse> {
+        self.request(Method::POST, &format!("/api/v1/jobs/{}/resume", job_id), None)
+            .await
+    }
+
+    /// Get job result
+    pub async fn get_job_result(&self, job_id: &str) -> ClientResult<serde_json::Value> {
+        self.request(
+            Method::GET,
+            &format!("/api/v1/jobs/{}/result", job_id),
+            None,
+        )
+        .await
+    }
+
+    /// List all jobs
+    pub async fn list_jobs(&self) -> ClientResult<JobListResponse> {
+        self.request(Method::GET, "/api/v1/jobs", None).await
+    }
+
+    /// Get worker capabilities
+    pub async fn get_capabilities(&self) -> ClientResult<CapabilitiesResponse> {
+        self.request(Method::GET, "/api/v1/capabilities", None)
+            .await
+    }
+
+    /// Health check
+    pub async fn health_check(&self) -> ClientResult<HealthResponse> {
+        self.request(Method::GET, "/api/v1/health", None).await
+    }
+
+    /// Stream a job's status, progress and state changes
+    ///
+    /// Opening the stream is retried like any other request; a stream that
+    /// breaks afterwards is not reopened.
+    pub async fn stream_events(&self, job_id: &str) -> ClientResult<JobEventStream> {
+        let url = format!("{}/api/v1/jobs/{}/events", self.base_url, job_id);
+
+        let response = self
+            .retrying(|| async {
+                let response = self
+                    .client
+                    .get(&url)
+                    .header(reqwest::header::ACCEPT, "text/event-stream")
+                    .send()
+                    .await?;
+                check_status(response).await
+            })
+            .await?;
+
+        Ok(JobEventStream::new(response))
+    }
+
+    /// Pick the job protocol version to use with the worker
+    pub async fn negotiate_protocol(&self) -> ClientResult<ProtocolVersion> {
+        let capabilities = self.get_capabilities().await?;
+
+        let theirs = if capabilities.protocol_versions.is_empty() {
+            vec![ProtocolVersion::V1]
+        } else {
+            capabilities
+                .protocol_versions
+                .iter()
+                .filter_map(|v| ProtocolVersion::parse(v).ok())
+                .collect()
+        };
+
+        negotiate(ProtocolVersion::SUPPORTED, &theirs).ok_or_else(|| {
+            ClientError::Protocol(format!(
+                "Worker accepts protocol versions {:?}, none of which this client supports",
+                capabilities.protocol_versions
+            ))
+        })
+    }
+
+    /// Send a request and decode the `data` of the API response
+    async fn request<T: DeserializeOwned>(
+        &self,
+        method: Method,
+        path: &str,
+        body: Option<&serde_json::Value>,
+    ) -> ClientResult<T> {
+        let url = format!("{}{}", self.base_url, path);
+
+        self.retrying(|| async {
+            let mut request = self.client.request(method.clone(), &url);
+            if let Some(body) = body {
+                request = request.json(body);
+            }
+
+            let response = check_status(request.send().await?).await?;
+            let api_response: ApiResponse<T> = serde_json::from_slice(&response.bytes().await?)?;
+            Ok(api_response.data)
+        })
+        .await
+    }
+
+    /// Run `attempt` until it succeeds or fails with a non-retryable error
+    async fn retrying<T, F, Fut>(&self, mut attempt: F) -> ClientResult<T>
+    where
+        F: FnMut() -> Fut,
+        Fut: Future<Output = ClientResult<T>>,
+    {
+        // Permanent errors end the retry loop as a successful outcome
+        retry_with_backoff(&self.retry, || {
+            let result = attempt();
+            async move {
+                match result.await {
+                    Err(e) if e.is_retryable() => Err(e),
+                    other => Ok(other),
+                }
+            }
+        })
+        .await
+        .and_then(|result| result)
+    }
+}
+
+/// Turn a non-success response into an API error
+async fn check_status(response: reqwest::Response) -> ClientResult<reqwest::Response> {
+    let status = response.status();
+    if status.is_success() {
+        return Ok(response);
+    }
+
+    let text = response.text().await.unwrap_or_default();
+    let message = match serde_json::from_str::<ApiErrorBody>(&text) {
+        Ok(body) => body.message,
+        Err(_) => text,
+    };
+
+    Err(ClientError::Api {
+        status: status.as_u16(),
+        message,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Serve one canned HTTP response per connection, in order
+    async fn serve(responses: Vec<(u16, &'static str)>) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+
+        tokio::spawn(async move {
+            for (status, body) in responses {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let mut request = [0u8; 4096];
+                let _ = socket.read(&mut request).await;
+
+                let response = format!(
+                    "HTTP/1.1 {} Status\r\ncontent-type: application/json\r\ncontent-length: {}\r\nconnection: close\r\n\r\n{}",
+                    status,
+                    body.len(),
+                    body
+                );
+                socket.write_all(response.as_bytes()).await.unwrap();
+            }
+        });
+
+        format!("http://{}", addr)
+    }
+
+    fn fast_retry() -> RetryConfig {
+        RetryConfig {
+            max_attempts: 3,
+            initial_delay: Duration::from_millis(10),
+            jitter: false,
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_retries_transient_errors() {
+        let url = serve(vec![
+            (503, "unavailable"),
+            (
+                200,
+                r#"{"success":true,"data":{"status":"healthy","uptime_seconds":42}}"#,
+            ),
+        ])
+        .await;
+
+        let client = WorkerClient::new(url).with_retry(fast_retry());
+        let health = client.health_check().await.unwrap();
+        assert_eq!(health.status, "healthy");
+        assert_eq!(health.uptime_seconds, 42);
+    }
+
+    #[tokio::test]
+    async fn test_api_error_not_retried() {
+        let url = serve(vec![(
+            404,
+            r#"{"error":"NOT_FOUND","message":"Job missing not found"}"#,
+        )])
+        .await;
+
+        let client = WorkerClient::new(url).with_retry(fast_retry());
+        let err = client.get_job_status("missing").await.unwrap_err();
+        assert!(!err.is_retryable());
+        assert_eq!(err.status(), Some(404));
+        assert!(err.to_string().contains("Job missing not found"));
+    }
+}
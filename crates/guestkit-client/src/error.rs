@@ -0,0 +1,51 @@
+//! Error types for the worker client
+
+use thiserror::Error;
+
+/// Worker client errors
+#[derive(Error, Debug)]
+pub enum ClientError {
+    #[error("HTTP error: {0}")]
+    Http(#[from] reqwest::Error),
+
+    #[error("API error ({status}): {message}")]
+    Api { status: u16, message: String },
+
+    #[error("Failed to parse response: {0}")]
+    Decode(#[from] serde_json::Error),
+
+    #[error("Job error: {0}")]
+    Job(#[from] guestkit_job_spec::JobError),
+
+    #[error("Protocol negotiation failed: {0}")]
+    Protocol(String),
+
+    #[error("IO error: {0}")]
+    Io(#[from] std::io::Error),
+}
+
+impl ClientError {
+    /// Check if the request may succeed when sent again
+    ///
+    /// Connection failures, timeouts, rate limiting and gateway errors are
+    /// transient; everything else is returned to the caller right away.
+    pub fn is_retryable(&self) -> bool {
+        match self {
+            ClientError::Http(e) => e.is_connect() || e.is_timeout(),
+            ClientError::Api { status, .. } => matches!(status, 429 | 502 | 503 | 504),
+            _ => false,
+        }
+    }
+
+    /// HTTP status of an API error
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            ClientError::Api { status, .. } => Some(*status),
+            ClientError::Http(e) => e.status().map(|s| s.as_u16()),
+            _ => None,
+        }
+    }
+}
+
+/// Result type for client operations
+pub type ClientResult<T> = std::result::Result<T, ClientError>;
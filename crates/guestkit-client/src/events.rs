@@ -0,0 +1,149 @@
+//! Server-sent event stream of a job
+
+use crate::error::{ClientError, ClientResult};
+use crate::types::JobEvent;
+
+/// Incremental parser for `text/event-stream` bodies
+///
+/// Feed it chunks as they arrive; complete events are returned as
+/// `(name, data)` pairs. Comments (keep-alives) are dropped, and events
+/// without a name are named `message` as in the SSE specification.
+#[derive(Debug, Default)]
+pub struct SseParser {
+    buffer: String,
+    name: Option<String>,
+    data: Vec<String>,
+}
+
+impl SseParser {
+    /// Create an empty parser
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse a chunk of the stream, returning the events it completes
+    pub fn feed(&mut self, chunk: &str) -> Vec<(String, String)> {
+        self.buffer.push_str(chunk);
+
+        let mut events = Vec::new();
+        while let Some(end) = self.buffer.find('\n') {
+            let line: String = self.buffer.drain(..=end).collect();
+            let line = line.trim_end_matches(['\n', '\r']);
+
+            if line.is_empty() {
+                if !self.data.is_empty() {
+                    let name = self.name.take().unwrap_or_else(|| "message".to_string());
+                    events.push((name, self.data.join("\n")));
+                }
+                self.name = None;
+                self.data.clear();
+                continue;
+            }
+
+            let (field, value) = match line.split_once(':') {
+                Some((field, value)) => (field, value.strip_prefix(' ').unwrap_or(value)),
+                None => (line, ""),
+            };
+            match field {
+                "" => {} // comment
+                "event" => self.name = Some(value.to_string()),
+                "data" => self.data.push(value.to_string()),
+                _ => {}
+            }
+        }
+
+        events
+    }
+}
+
+/// Events of one job, read from an open SSE response
+///
+/// Starts with the job's status and ends after the event that moves the job
+/// to a terminal state (or when the worker closes the stream).
+pub struct JobEventStream {
+    response: reqwest::Response,
+    parser: SseParser,
+    pending: std::collections::VecDeque<JobEvent>,
+    finished: bool,
+}
+
+impl JobEventStream {
+    pub(crate) fn new(response: reqwest::Response) -> Self {
+        Self {
+            response,
+            parser: SseParser::new(),
+            pending: Default::default(),
+            finished: false,
+        }
+    }
+
+    /// Next event, or `None` once the stream has ended
+    pub async fn next(&mut self) -> Option<ClientResult<JobEvent>> {
+        loop {
+            if let Some(event) = self.pending.pop_front() {
+                if event.is_terminal() {
+                    self.pending.clear();
+                    self.finished = true;
+                }
+                return Some(Ok(event));
+            }
+            if self.finished {
+                return None;
+            }
+
+            let chunk = match self.response.chunk().await {
+                Ok(Some(chunk)) => chunk,
+                Ok(None) => {
+                    self.finished = true;
+                    return None;
+                }
+                Err(e) => {
+                    self.finished = true;
+                    return Some(Err(ClientError::Http(e)));
+                }
+            };
+
+            for (name, data) in self.parser.feed(&String::from_utf8_lossy(&chunk)) {
+                match JobEvent::from_sse(&name, &data) {
+                    Ok(Some(event)) => self.pending.push_back(event),
+                    Ok(None) => {}
+                    Err(e) => return Some(Err(ClientError::Decode(e))),
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sse_parser_split_chunks() {
+        let mut parser = SseParser::new();
+
+        assert!(parser.feed("event: state\nda").is_empty());
+        let events = parser.feed("ta: {\"to\":\"running\"}\n\n: keep-alive\n\n");
+        assert_eq!(
+            events,
+            vec![("state".to_string(), "{\"to\":\"running\"}".to_string())]
+        );
+
+        let events = parser.feed("data: a\r\ndata: b\r\n\r\n");
+        assert_eq!(events, vec![("message".to_string(), "a\nb".to_string())]);
+    }
+
+    #[test]
+    fn test_job_event_from_sse() {
+        let state = r#"{"type":"state_changed","job_id":"job-1","from":"running","to":"completed","timestamp":"2026-01-01T00:00:00Z"}"#;
+        let event = JobEvent::from_sse("state", state).unwrap().unwrap();
+        assert!(event.is_terminal());
+
+        let status = r#"{"job_id":"job-1","status":"running","operation":"guestkit.inspect","submitted_at":"2026-01-01T00:00:00Z","started_at":null,"completed_at":null,"worker_id":null,"error":null}"#;
+        let event = JobEvent::from_sse("status", status).unwrap().unwrap();
+        assert!(!event.is_terminal());
+
+        assert!(JobEvent::from_sse("heartbeat", "{}").unwrap().is_none());
+        assert!(JobEvent::from_sse("state", "not json").is_err());
+    }
+}
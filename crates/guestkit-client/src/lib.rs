@@ -0,0 +1,46 @@
+//! Guestkit Client - REST API client for guestkit workers
+//!
+//! Typed async methods to submit jobs, query their status, stream their
+//! events, fetch results and cancel, pause or resume them. Transient
+//! failures are retried with backoff. Enable the `blocking` feature for
+//! synchronous wrappers.
+//!
+//! ```no_run
+//! use guestkit_client::WorkerClient;
+//! use guestkit_job_spec::builder::inspect_job;
+//!
+//! # async fn run() -> Result<(), Box<dyn std::error::Error>> {
+//! let client = WorkerClient::new("http://localhost:8080");
+//!
+//! let job = inspect_job("/vms/production.qcow2").build()?;
+//! let submitted = client.submit_job(job).await?;
+//!
+//! let mut events = client.stream_events(&submitted.job_id).await?;
+//! while let Some(event) = events.next().await {
+//!     println!("{:?}", event?);
+//! }
+//!
+//! let result = client.get_job_result(&submitted.job_id).await?;
+//! println!("{}", result);
+//! # Ok(())
+//! # }
+//! ```
+
+pub mod client;
+pub mod error;
+pub mod events;
+pub mod types;
+
+#[cfg(feature = "blocking")]
+pub mod blocking;
+
+// Re-exports
+pub use client::WorkerClient;
+pub use error::{ClientError, ClientResult};
+pub use events::{JobEventStream, SseParser};
+pub use guestkit::core::retry::RetryConfig;
+pub use types::{
+    ApiResponse, CapabilitiesResponse, HealthResponse, JobCancelResponse, JobControlResponse,
+    JobEvent, JobListResponse, JobStatusResponse, JobSubmitRequest, JobSubmitResponse,
+    StateChange,
+};
@@ -0,0 +1,147 @@
+//! Request and response types of the worker REST API
+
+use guestkit_job_spec::ProgressEvent;
+use serde::{Deserialize, Serialize};
+
+/// Job states after which a job's event stream ends
+pub const TERMINAL_STATES: [&str; 4] = ["completed", "failed", "cancelled", "timeout"];
+
+/// API response wrapper
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ApiResponse<T> {
+    pub success: bool,
+    pub data: T,
+}
+
+/// Job submission request
+#[derive(Debug, Serialize)]
+pub struct JobSubmitRequest {
+    /// Job document in the negotiated protocol version
+    #[serde(flatten)]
+    pub job: serde_json::Value,
+}
+
+/// Job submission response
+#[derive(Debug, Deserialize, Serialize)]
+pub struct JobSubmitResponse {
+    pub job_id: String,
+    pub status: String,
+    pub message: String,
+}
+
+/// Job status response
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct JobStatusResponse {
+    pub job_id: String,
+    pub status: String,
+    pub operation: String,
+    pub submitted_at: String,
+    pub started_at: Option<String>,
+    pub completed_at: Option<String>,
+    pub worker_id: Option<String>,
+    pub error: Option<String>,
+}
+
+/// Job cancellation response
+#[derive(Debug, Deserialize, Serialize)]
+pub struct JobCancelResponse {
+    pub job_id: String,
+    pub status: String,
+    pub message: String,
+}
+
+/// Job pause/resume response
+#[derive(Debug, Deserialize, Serialize)]
+pub struct JobControlResponse {
+    pub job_id: String,
+    pub status: String,
+    pub message: String,
+}
+
+/// Job list response
+#[derive(Debug, Deserialize, Serialize)]
+pub struct JobListResponse {
+    pub jobs: Vec<JobStatusResponse>,
+    pub total: usize,
+}
+
+/// Worker capabilities response
+#[derive(Debug, Deserialize, Serialize)]
+pub struct CapabilitiesResponse {
+    pub worker_id: String,
+    pub pool: Option<String>,
+    pub operations: Vec<String>,
+    pub features: Vec<String>,
+    pub disk_formats: Vec<String>,
+    /// Accepted job protocol versions (missing from workers that only speak 1.0)
+    #[serde(default)]
+    pub protocol_versions: Vec<String>,
+    /// Resource limits (zero when unknown)
+    #[serde(default)]
+    pub max_disk_size_gb: u64,
+    #[serde(default)]
+    pub cpu_cores: u32,
+    #[serde(default)]
+    pub memory_gb: u64,
+}
+
+/// Health check response
+#[derive(Debug, Deserialize, Serialize)]
+pub struct HealthResponse {
+    pub status: String,
+    pub uptime_seconds: u64,
+}
+
+/// Error body returned with non-success responses
+#[derive(Debug, Deserialize)]
+pub(crate) struct ApiErrorBody {
+    pub message: String,
+}
+
+/// State transition of a job
+#[derive(Debug, Clone, Deserialize, Serialize)]
+pub struct StateChange {
+    pub job_id: String,
+    pub from: String,
+    pub to: String,
+    pub timestamp: String,
+}
+
+/// Event streamed from `/api/v1/jobs/:id/events`
+#[derive(Debug, Clone)]
+pub enum JobEvent {
+    /// Job status when the stream was opened
+    Status(JobStatusResponse),
+
+    /// Progress reported by the job's handler
+    Progress(Box<ProgressEvent>),
+
+    /// Job state machine transition
+    StateChanged(StateChange),
+}
+
+impl JobEvent {
+    /// Decode an event from its SSE name and JSON data
+    ///
+    /// Returns `Ok(None)` for event names this client does not know, so newer
+    /// workers can add events without breaking older clients.
+    pub fn from_sse(name: &str, data: &str) -> serde_json::Result<Option<Self>> {
+        let event = match name {
+            "status" => JobEvent::Status(serde_json::from_str(data)?),
+            "progress" => JobEvent::Progress(Box::new(serde_json::from_str(data)?)),
+            "state" => JobEvent::StateChanged(serde_json::from_str(data)?),
+            _ => return Ok(None),
+        };
+        Ok(Some(event))
+    }
+
+    /// Check if this event ends the job's event stream
+    pub fn is_terminal(&self) -> bool {
+        let state = match self {
+            JobEvent::Status(status) => &status.status,
+            JobEvent::StateChanged(change) => &change.to,
+            JobEvent::Progress(_) => return false,
+        };
+        TERMINAL_STATES.contains(&state.as_str())
+    }
+}
@@ -0,0 +1,55 @@
+//! Guestkit Job Spec CLI
+//!
+//! Emits JSON Schema for the job protocol documents.
+//!
+//! # Usage
+//!
+//! - `guestkit-job-spec schema <DOCUMENT>` - Print the schema of one document
+//! - `guestkit-job-spec schema --out-dir <DIR>` - Write all schemas to `DIR`
+
+use std::path::Path;
+
+use anyhow::{bail, Context};
+use guestkit_job_spec::schema::{schema_for_document, DOCUMENTS};
+
+fn usage() -> String {
+    format!(
+        "Usage: guestkit-job-spec schema <DOCUMENT>\n       \
+         guestkit-job-spec schema --out-dir <DIR>\n\n\
+         Documents: {}",
+        DOCUMENTS.join(", ")
+    )
+}
+
+fn main() -> anyhow::Result<()> {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let args: Vec<&str> = args.iter().map(String::as_str).collect();
+
+    match args.as_slice() {
+        ["schema", "--out-dir", dir] => write_all(Path::new(dir)),
+        ["schema", name] if !name.starts_with('-') => {
+            let schema = schema_for_document(name)
+                .with_context(|| format!("unknown document '{}'\n\n{}", name, usage()))?;
+            println!("{}", serde_json::to_string_pretty(&schema)?);
+            Ok(())
+        }
+        ["-h" | "--help" | "help"] => {
+            println!("{}", usage());
+            Ok(())
+        }
+        _ => bail!("{}", usage()),
+    }
+}
+
+fn write_all(dir: &Path) -> anyhow::Result<()> {
+    std::fs::create_dir_all(dir).with_context(|| format!("failed to create {}", dir.display()))?;
+
+    for name in DOCUMENTS {
+        let schema = schema_for_document(name).expect("listed document has a schema");
+        let path = dir.join(format!("{}.schema.json", name));
+        std::fs::write(&path, serde_json::to_string_pretty(&schema)? + "\n")
+            .with_context(|| format!("failed to write {}", path.display()))?;
+        println!("Wrote {}", path.display());
+    }
+    Ok(())
+}
@@ -2,6 +2,7 @@
 
 use crate::types::*;
 use crate::error::{JobError, JobResult};
+use crate::payloads::{ConvertPayload, FixPayload, InspectPayload, OperationPayload};
 use crate::PROTOCOL_VERSION;
 use chrono::Utc;
 use std::collections::HashMap;
@@ -13,10 +14,12 @@ pub struct JobBuilder {
     operation: Option<String>,
     payload_type: Option<String>,
     payload_data: Option<serde_json::Value>,
+    payload_check: Option<fn(&Payload) -> JobResult<()>>,
     metadata: JobMetadata,
     execution: ExecutionPolicy,
     constraints: Constraints,
     routing: Routing,
+    depends_on: Vec<String>,
     observability: Observability,
     audit: Audit,
 }
@@ -56,6 +59,18 @@ impl JobBuilder {
     ) -> Self {
         self.payload_type = Some(payload_type.into());
         self.payload_data = Some(data);
+        self.payload_check = None;
+        self
+    }
+
+    /// Set a typed payload and the operation it belongs to
+    ///
+    /// The payload's own checks run when the job is built.
+    pub fn typed_payload<P: OperationPayload>(mut self, payload: P) -> Self {
+        self.operation = Some(P::OPERATION.to_string());
+        self.payload_type = Some(P::PAYLOAD_TYPE.to_string());
+        self.payload_data = Some(serde_json::to_value(&payload).unwrap_or_default());
+        self.payload_check = Some(|payload| P::from_payload(payload).map(|_| ()));
         self
     }
 
@@ -113,6 +128,21 @@ impl JobBuilder {
         self
     }
 
+    /// Run on a recurring schedule (cron expression or RRULE)
+    pub fn schedule(mut self, expression: impl Into<String>) -> Self {
+        self.execution.schedule = Some(JobSchedule::new(expression));
+        self
+    }
+
+    /// Set schedule jitter and overlap policy
+    pub fn schedule_policy(mut self, jitter_seconds: u64, overlap: OverlapPolicy) -> Self {
+        if let Some(ref mut schedule) = self.execution.schedule {
+            schedule.jitter_seconds = jitter_seconds;
+            schedule.overlap = overlap;
+        }
+        self
+    }
+
     /// Add required capability
     pub fn require_capability(mut self, capability: impl Into<String>) -> Self {
         self.constraints
@@ -131,12 +161,39 @@ impl JobBuilder {
         self
     }
 
+    /// Require a worker that handles disks of this size (GB)
+    pub fn max_disk_size_gb(mut self, size_gb: u64) -> Self {
+        self.constraints.maximum_disk_size_gb = Some(size_gb);
+        self
+    }
+
+    /// Require a worker with at least this many CPU cores and this much memory (GB)
+    pub fn min_resources(mut self, cpu_cores: u32, memory_gb: u64) -> Self {
+        self.constraints.min_cpu_cores = Some(cpu_cores);
+        self.constraints.min_memory_gb = Some(memory_gb);
+        self
+    }
+
+    /// Cap the job's disk bandwidth (MB/s) and set its I/O scheduling class
+    /// ("idle", "best-effort" or "best-effort:0-7")
+    pub fn limit_io(mut self, bandwidth_mb_per_sec: Option<u64>, priority: Option<&str>) -> Self {
+        self.constraints.max_io_bandwidth_mb_per_sec = bandwidth_mb_per_sec;
+        self.constraints.io_priority = priority.map(String::from);
+        self
+    }
+
     /// Set worker pool
     pub fn worker_pool(mut self, pool: impl Into<String>) -> Self {
         self.routing.worker_pool = Some(pool.into());
         self
     }
 
+    /// Run only after the given job has completed successfully
+    pub fn depends_on(mut self, job_id: impl Into<String>) -> Self {
+        self.depends_on.push(job_id.into());
+        self
+    }
+
     /// Set trace ID
     pub fn trace_id(mut self, trace_id: impl Into<String>) -> Self {
         self.observability.trace_id = Some(trace_id.into());
@@ -174,7 +231,7 @@ impl JobBuilder {
             .ok_or_else(|| JobError::MissingField("payload.data".to_string()))?;
 
         let job = JobDocument {
-            schema: Some("https://guestkit.dev/schemas/job-v1.json".to_string()),
+            schema: Some("https://guestkit.dev/schemas/job-v2.json".to_string()),
             version: PROTOCOL_VERSION.to_string(),
             job_id,
             created_at: Utc::now(),
@@ -196,6 +253,7 @@ impl JobBuilder {
             } else {
                 None
             },
+            depends_on: self.depends_on,
             payload: Payload {
                 payload_type,
                 data: payload_data,
@@ -215,34 +273,42 @@ impl JobBuilder {
         // Validate the built job
         crate::validation::JobValidator::validate(&job)?;
 
+        if let Some(check) = self.payload_check {
+            check(&job.payload)?;
+        }
+
         Ok(job)
     }
 }
 
 /// Helper to create a guestkit inspect job
 pub fn inspect_job(image_path: impl Into<String>) -> JobBuilder {
-    let payload = serde_json::json!({
-        "image": {
-            "path": image_path.into(),
-            "format": "qcow2",
-            "read_only": true
-        },
-        "options": {
-            "deep_scan": false,
-            "include_packages": true,
-            "include_services": true,
-            "include_network": true,
-            "include_security": true
-        }
-    });
-
     JobBuilder::new()
         .generate_job_id()
-        .operation("guestkit.inspect")
-        .payload("guestkit.inspect.v1", payload)
+        .typed_payload(InspectPayload::new(image_path, "qcow2"))
         .require_capability("guestkit.inspect")
 }
 
+/// Helper to create a guestkit convert job
+pub fn convert_job(
+    source: impl Into<String>,
+    target: impl Into<String>,
+    format: impl Into<String>,
+) -> JobBuilder {
+    JobBuilder::new()
+        .generate_job_id()
+        .typed_payload(ConvertPayload::new(source, target, format))
+        .require_capability("guestkit.convert")
+}
+
+/// Helper to create a guestkit fix job
+pub fn fix_job(image_path: impl Into<String>, plan: serde_json::Value) -> JobBuilder {
+    JobBuilder::new()
+        .generate_job_id()
+        .typed_payload(FixPayload::new(image_path, plan))
+        .require_capability("guestkit.fix")
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -258,7 +324,7 @@ mod tests {
 
         assert_eq!(job.job_id, "job-test-123");
         assert_eq!(job.operation, "guestkit.inspect");
-        assert_eq!(job.version, "1.0");
+        assert_eq!(job.version, "2.0");
         assert_eq!(job.kind, "VMOperation");
     }
 
@@ -296,12 +362,15 @@ mod tests {
             .require_capability("disk.qcow2")
             .require_feature("lvm")
             .require_feature("selinux")
+            .limit_io(Some(200), Some("idle"))
             .build()
             .unwrap();
 
         let constraints = job.constraints.unwrap();
         assert_eq!(constraints.required_capabilities.unwrap().len(), 2);
         assert_eq!(constraints.required_features.unwrap().len(), 2);
+        assert_eq!(constraints.max_io_bandwidth_mb_per_sec, Some(200));
+        assert_eq!(constraints.io_priority.as_deref(), Some("idle"));
     }
 
     #[test]
@@ -313,6 +382,25 @@ mod tests {
         assert!(job.job_id.starts_with("job-"));
     }
 
+    #[test]
+    fn test_typed_payload_helpers() {
+        let job = convert_job("/vms/a.vmdk", "/vms/a.qcow2", "qcow2")
+            .build()
+            .unwrap();
+        assert_eq!(job.operation, "guestkit.convert");
+        assert_eq!(job.payload.payload_type, "guestkit.convert.v1");
+        assert_eq!(job.payload.data["target"]["format"], "qcow2");
+
+        let result = fix_job("", serde_json::json!({})).build();
+        assert!(matches!(result, Err(JobError::MissingField(f)) if f == "payload.data.image.path"));
+
+        // An untyped payload replaces the typed one and its checks
+        let job = convert_job("/vms/a.vmdk", "/vms/a.qcow2", "iso")
+            .payload("guestkit.convert.v1", serde_json::json!({}))
+            .build();
+        assert!(job.is_ok());
+    }
+
     #[test]
     fn test_builder_missing_operation() {
         let result = JobBuilder::new()
@@ -35,6 +35,15 @@ pub enum JobError {
     #[error("Invalid timestamp: {0}")]
     InvalidTimestamp(String),
 
+    #[error("Dependency cycle detected: {}", .0.join(" -> "))]
+    DependencyCycle(Vec<String>),
+
+    #[error("Invalid job signature: {0}")]
+    InvalidSignature(String),
+
+    #[error("Job is not signed")]
+    UnsignedJob,
+
     #[error("Capability mismatch: required {required:?}, available {available:?}")]
     CapabilityMismatch {
         required: Vec<String>,
@@ -0,0 +1,141 @@
+//! Job dependency graph ordering
+
+use crate::error::{JobError, JobResult};
+use crate::types::JobDocument;
+use std::collections::{BTreeSet, HashMap};
+
+/// Order jobs so every job comes after the jobs it depends on
+///
+/// Dependencies on jobs outside `jobs` are treated as external (e.g. already
+/// submitted earlier) and do not constrain the order. Jobs without ordering
+/// constraints keep their input order.
+pub fn topological_order(jobs: &[JobDocument]) -> JobResult<Vec<&JobDocument>> {
+    let index: HashMap<&str, usize> = jobs
+        .iter()
+        .enumerate()
+        .map(|(i, job)| (job.job_id.as_str(), i))
+        .collect();
+
+    if index.len() != jobs.len() {
+        return Err(JobError::ValidationError(
+            "duplicate job_id in dependency graph".to_string(),
+        ));
+    }
+
+    let mut in_degree = vec![0usize; jobs.len()];
+    let mut dependents: Vec<Vec<usize>> = vec![Vec::new(); jobs.len()];
+
+    for (i, job) in jobs.iter().enumerate() {
+        for dep in &job.depends_on {
+            if let Some(&d) = index.get(dep.as_str()) {
+                in_degree[i] += 1;
+                dependents[d].push(i);
+            }
+        }
+    }
+
+    let mut ready: BTreeSet<usize> = (0..jobs.len()).filter(|&i| in_degree[i] == 0).collect();
+    let mut ordered = Vec::with_capacity(jobs.len());
+
+    while let Some(i) = ready.pop_first() {
+        ordered.push(&jobs[i]);
+        for &next in &dependents[i] {
+            in_degree[next] -= 1;
+            if in_degree[next] == 0 {
+                ready.insert(next);
+            }
+        }
+    }
+
+    if ordered.len() != jobs.len() {
+        return Err(JobError::DependencyCycle(find_cycle(jobs, &index, &in_degree)));
+    }
+
+    Ok(ordered)
+}
+
+/// Walk the unresolved jobs to report one concrete cycle
+fn find_cycle(jobs: &[JobDocument], index: &HashMap<&str, usize>, in_degree: &[usize]) -> Vec<String> {
+    // Every unresolved job still has at least one unresolved dependency,
+    // so following those edges must eventually revisit a job.
+    let mut current = match in_degree.iter().position(|&d| d > 0) {
+        Some(i) => i,
+        None => return Vec::new(),
+    };
+    let mut path: Vec<usize> = Vec::new();
+
+    loop {
+        if let Some(start) = path.iter().position(|&i| i == current) {
+            let mut cycle: Vec<String> = path[start..]
+                .iter()
+                .map(|&i| jobs[i].job_id.clone())
+                .collect();
+            cycle.push(jobs[current].job_id.clone());
+            return cycle;
+        }
+
+        path.push(current);
+        current = match jobs[current]
+            .depends_on
+            .iter()
+            .filter_map(|dep| index.get(dep.as_str()).copied())
+            .find(|&d| in_degree[d] > 0)
+        {
+            Some(next) => next,
+            None => return path.iter().map(|&i| jobs[i].job_id.clone()).collect(),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::builder::JobBuilder;
+
+    fn job(id: &str, deps: &[&str]) -> JobDocument {
+        let mut job = JobBuilder::new()
+            .job_id(id)
+            .operation("guestkit.inspect")
+            .payload("guestkit.inspect.v1", serde_json::json!({}))
+            .build()
+            .unwrap();
+        job.depends_on = deps.iter().map(|d| d.to_string()).collect();
+        job
+    }
+
+    #[test]
+    fn test_topological_order() {
+        let jobs = vec![
+            job("job-convert", &["job-inspect"]),
+            job("job-profile", &[]),
+            job("job-inspect", &["job-external"]),
+        ];
+
+        let ordered: Vec<&str> = topological_order(&jobs)
+            .unwrap()
+            .iter()
+            .map(|j| j.job_id.as_str())
+            .collect();
+
+        assert_eq!(ordered, vec!["job-profile", "job-inspect", "job-convert"]);
+    }
+
+    #[test]
+    fn test_cycle_detection() {
+        let jobs = vec![
+            job("job-aaaaaaaa", &["job-cccccccc"]),
+            job("job-bbbbbbbb", &["job-aaaaaaaa"]),
+            job("job-cccccccc", &["job-bbbbbbbb"]),
+            job("job-dddddddd", &[]),
+        ];
+
+        match topological_order(&jobs) {
+            Err(JobError::DependencyCycle(cycle)) => {
+                assert_eq!(cycle.len(), 4);
+                assert_eq!(cycle.first(), cycle.last());
+                assert!(!cycle.contains(&"job-dddddddd".to_string()));
+            }
+            other => panic!("expected cycle, got {:?}", other.map(|o| o.len())),
+        }
+    }
+}
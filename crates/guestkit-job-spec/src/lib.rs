@@ -1,12 +1,21 @@
 //! VM Operations Job Protocol - Type definitions and validation
 //!
-//! This crate provides the type definitions for the VM Operations Job Protocol v1.
-//! It supports serialization/deserialization and validation of job specifications.
+//! This crate provides the type definitions for the VM Operations Job Protocol v2.
+//! It supports serialization/deserialization and validation of job specifications,
+//! and upconverts v1 documents (see [`version`]).
 
 pub mod error;
 pub mod types;
 pub mod validation;
 pub mod builder;
+pub mod graph;
+pub mod payloads;
+pub mod signature;
+pub mod v1;
+pub mod version;
+
+#[cfg(feature = "schema-gen")]
+pub mod schema;
 
 // Re-export main types
 pub use error::{JobError, JobResult};
@@ -15,12 +24,17 @@ pub use types::{
     Routing, Observability, Audit, Payload, WorkerCapabilities,
     JobResult as JobResultType, ProgressEvent, JobStatus,
     ExecutionSummary, JobOutputs, JobExecutionError, ExecutionMetrics,
+    JobSchedule, OverlapPolicy, JobSignature,
 };
 pub use validation::JobValidator;
 pub use builder::JobBuilder;
+pub use graph::topological_order;
+pub use payloads::OperationPayload;
+pub use signature::{JobSigner, SignaturePolicy, TrustAnchor, TrustAnchors};
+pub use version::{negotiate, ProtocolVersion};
 
 /// Protocol version
-pub const PROTOCOL_VERSION: &str = "1.0";
+pub const PROTOCOL_VERSION: &str = "2.0";
 
 /// Operation namespaces
 pub mod operations {
@@ -46,6 +60,7 @@ mod tests {
 
     #[test]
     fn test_protocol_version() {
-        assert_eq!(PROTOCOL_VERSION, "1.0");
+        assert_eq!(PROTOCOL_VERSION, "2.0");
+        assert_eq!(PROTOCOL_VERSION, ProtocolVersion::CURRENT.to_string());
     }
 }
@@ -0,0 +1,647 @@
+//! Typed payloads for the guestkit operations
+//!
+//! [`Payload::data`] is free-form JSON on the wire. The types here give each
+//! operation a checked structure, so producers can build payloads with the
+//! compiler's help and workers can decode them without hand-written
+//! `serde_json::Value` plumbing:
+//!
+//! ```
+//! use guestkit_job_spec::payloads::{ConvertPayload, InspectPayload};
+//! use guestkit_job_spec::JobBuilder;
+//!
+//! let job = JobBuilder::new()
+//!     .generate_job_id()
+//!     .typed_payload(InspectPayload::new("/vms/web-01.qcow2", "qcow2"))
+//!     .build()
+//!     .unwrap();
+//! assert_eq!(job.operation, "guestkit.inspect");
+//!
+//! let payload: InspectPayload = job.typed_payload().unwrap();
+//! assert_eq!(payload.image.path, "/vms/web-01.qcow2");
+//!
+//! // Converting to an unsupported format fails when the job is built
+//! let result = JobBuilder::new()
+//!     .generate_job_id()
+//!     .typed_payload(ConvertPayload::new("/vms/a.vmdk", "/vms/a.img", "iso"))
+//!     .build();
+//! assert!(result.is_err());
+//! ```
+
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{JobError, JobResult};
+use crate::operations;
+use crate::types::{JobDocument, Payload};
+
+/// Payload of a specific operation
+pub trait OperationPayload: Serialize + DeserializeOwned {
+    /// Operation the payload belongs to (e.g., "guestkit.inspect")
+    const OPERATION: &'static str;
+
+    /// Payload type on the wire (namespace.operation.version)
+    const PAYLOAD_TYPE: &'static str;
+
+    /// Check constraints the type system cannot express
+    fn validate(&self) -> JobResult<()> {
+        Ok(())
+    }
+
+    /// Wrap the payload in its wire form
+    fn to_payload(&self) -> JobResult<Payload> {
+        Ok(Payload {
+            payload_type: Self::PAYLOAD_TYPE.to_string(),
+            data: serde_json::to_value(self)?,
+        })
+    }
+
+    /// Decode and validate a wire payload of this type
+    fn from_payload(payload: &Payload) -> JobResult<Self> {
+        if payload.payload_type != Self::PAYLOAD_TYPE {
+            return Err(JobError::InvalidField {
+                field: "payload.type".to_string(),
+                reason: format!(
+                    "expected '{}', got '{}'",
+                    Self::PAYLOAD_TYPE,
+                    payload.payload_type
+                ),
+            });
+        }
+
+        let typed: Self =
+            serde_json::from_value(payload.data.clone()).map_err(|e| JobError::InvalidField {
+                field: "payload.data".to_string(),
+                reason: e.to_string(),
+            })?;
+        typed.validate()?;
+        Ok(typed)
+    }
+}
+
+impl JobDocument {
+    /// Decode the payload as `P`, checking it belongs to the job's operation
+    pub fn typed_payload<P: OperationPayload>(&self) -> JobResult<P> {
+        if self.operation != P::OPERATION {
+            return Err(JobError::InvalidField {
+                field: "operation".to_string(),
+                reason: format!(
+                    "expected '{}' for payload '{}', got '{}'",
+                    P::OPERATION,
+                    P::PAYLOAD_TYPE,
+                    self.operation
+                ),
+            });
+        }
+        P::from_payload(&self.payload)
+    }
+}
+
+fn default_true() -> bool {
+    true
+}
+
+fn require_path(field: &str, path: &str) -> JobResult<()> {
+    if path.is_empty() {
+        return Err(JobError::MissingField(field.to_string()));
+    }
+    Ok(())
+}
+
+// ========================================
+// guestkit.inspect
+// ========================================
+
+/// Guestkit inspect payload (v1)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
+pub struct InspectPayload {
+    pub image: ImageSpec,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub options: Option<InspectOptions>,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub output: Option<OutputSpec>,
+}
+
+impl InspectPayload {
+    /// Inspect an image read-only with the default options
+    pub fn new(path: impl Into<String>, format: impl Into<String>) -> Self {
+        Self {
+            image: ImageSpec {
+                path: path.into(),
+                format: format.into(),
+                checksum: None,
+                size_bytes: None,
+                read_only: true,
+            },
+            options: Some(InspectOptions::default()),
+            output: None,
+        }
+    }
+
+    /// Verify the image against a checksum before inspecting it
+    pub fn with_checksum(mut self, checksum: impl Into<String>) -> Self {
+        self.image.checksum = Some(checksum.into());
+        self
+    }
+
+    /// Set inspect options
+    pub fn with_options(mut self, options: InspectOptions) -> Self {
+        self.options = Some(options);
+        self
+    }
+
+    /// Write the report to a destination
+    pub fn with_output(
+        mut self,
+        format: impl Into<String>,
+        destination: impl Into<String>,
+    ) -> Self {
+        self.output = Some(OutputSpec {
+            format: format.into(),
+            destination: destination.into(),
+            compression: None,
+        });
+        self
+    }
+}
+
+impl OperationPayload for InspectPayload {
+    const OPERATION: &'static str = operations::GUESTKIT_INSPECT;
+    const PAYLOAD_TYPE: &'static str = "guestkit.inspect.v1";
+
+    fn validate(&self) -> JobResult<()> {
+        require_path("payload.data.image.path", &self.image.path)
+    }
+}
+
+/// Image specification
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
+pub struct ImageSpec {
+    /// Path to image file
+    pub path: String,
+
+    /// Image format (qcow2, vmdk, etc.)
+    pub format: String,
+
+    /// Checksum for verification (SHA256)
+    ///
+    /// Supports two formats:
+    /// - `"sha256:hexhash"` - Explicit algorithm specification
+    /// - `"hexhash"` - Defaults to SHA256
+    ///
+    /// Example: `"sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"`
+    ///
+    /// When provided, the worker will compute the SHA256 hash of the image file
+    /// and verify it matches the expected value before processing. This protects
+    /// against corrupted or tampered images.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+
+    /// Image size in bytes
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub size_bytes: Option<u64>,
+
+    /// Whether to open read-only
+    #[serde(default = "default_true")]
+    pub read_only: bool,
+}
+
+/// Inspect options
+///
+/// Defaults match the worker: everything except deep scans and databases.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
+#[serde(default)]
+pub struct InspectOptions {
+    pub deep_scan: bool,
+    pub include_packages: bool,
+    pub include_services: bool,
+    pub include_users: bool,
+    pub include_network: bool,
+    pub include_security: bool,
+    pub include_storage: bool,
+    pub include_databases: bool,
+}
+
+impl Default for InspectOptions {
+    fn default() -> Self {
+        Self {
+            deep_scan: false,
+            include_packages: true,
+            include_services: true,
+            include_users: true,
+            include_network: true,
+            include_security: true,
+            include_storage: true,
+            include_databases: false,
+        }
+    }
+}
+
+/// Output specification
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
+pub struct OutputSpec {
+    /// Output format (json, yaml, etc.)
+    pub format: String,
+
+    /// Destination path
+    pub destination: String,
+
+    /// Optional compression
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression: Option<String>,
+}
+
+// ========================================
+// guestkit.convert
+// ========================================
+
+/// Disk formats a conversion can write
+pub const CONVERT_TARGET_FORMATS: [&str; 5] = ["qcow2", "raw", "vmdk", "vdi", "vhdx"];
+
+/// URI schemes of the places a converted image can be published to
+pub const PUBLISH_SCHEMES: [&str; 2] = ["glance", "proxmox"];
+
+/// Guestkit convert payload (v1)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
+pub struct ConvertPayload {
+    pub source: ConvertSource,
+    pub target: ConvertTarget,
+    #[serde(default)]
+    pub options: ConvertOptions,
+}
+
+impl ConvertPayload {
+    /// Convert `source` to `target` in `format`, detecting the source format
+    pub fn new(
+        source: impl Into<String>,
+        target: impl Into<String>,
+        format: impl Into<String>,
+    ) -> Self {
+        Self {
+            source: ConvertSource {
+                path: source.into(),
+                format: None,
+                checksum: None,
+            },
+            target: ConvertTarget {
+                path: target.into(),
+                format: format.into(),
+                compression: false,
+                compression_type: None,
+            },
+            options: ConvertOptions::default(),
+        }
+    }
+
+    /// Set the source format instead of detecting it
+    pub fn with_source_format(mut self, format: impl Into<String>) -> Self {
+        self.source.format = Some(format.into());
+        self
+    }
+
+    /// Verify the source against a checksum before converting
+    pub fn with_checksum(mut self, checksum: impl Into<String>) -> Self {
+        self.source.checksum = Some(checksum.into());
+        self
+    }
+
+    /// Compress the target (qcow2 only)
+    pub fn with_compression(mut self, compression_type: Option<String>) -> Self {
+        self.target.compression = true;
+        self.target.compression_type = compression_type;
+        self
+    }
+
+    /// Replace an existing target
+    pub fn with_overwrite(mut self, overwrite: bool) -> Self {
+        self.options.overwrite = overwrite;
+        self
+    }
+
+    /// Upload the converted image to `target` (`glance://...` or `proxmox://...`)
+    pub fn with_publish(mut self, target: impl Into<String>) -> Self {
+        self.options.publish.push(target.into());
+        self
+    }
+}
+
+impl OperationPayload for ConvertPayload {
+    const OPERATION: &'static str = operations::GUESTKIT_CONVERT;
+    const PAYLOAD_TYPE: &'static str = "guestkit.convert.v1";
+
+    fn validate(&self) -> JobResult<()> {
+        require_path("payload.data.source.path", &self.source.path)?;
+        require_path("payload.data.target.path", &self.target.path)?;
+
+        if self.source.path == self.target.path {
+            return Err(JobError::InvalidField {
+                field: "payload.data.target.path".to_string(),
+                reason: "must differ from the source path".to_string(),
+            });
+        }
+
+        if !CONVERT_TARGET_FORMATS.contains(&self.target.format.as_str()) {
+            return Err(JobError::InvalidField {
+                field: "payload.data.target.format".to_string(),
+                reason: format!(
+                    "must be one of {}, got '{}'",
+                    CONVERT_TARGET_FORMATS.join(", "),
+                    self.target.format
+                ),
+            });
+        }
+
+        if self.target.compression && self.target.format != "qcow2" {
+            return Err(JobError::InvalidField {
+                field: "payload.data.target.compression".to_string(),
+                reason: "only supported for qcow2 targets".to_string(),
+            });
+        }
+
+        for (i, target) in self.options.publish.iter().enumerate() {
+            let scheme = target.split_once("://").map(|(scheme, _)| scheme);
+            if !scheme.is_some_and(|scheme| PUBLISH_SCHEMES.contains(&scheme)) {
+                return Err(JobError::InvalidField {
+                    field: format!("payload.data.options.publish[{}]", i),
+                    reason: format!(
+                        "must be a {} URI, got '{}'",
+                        PUBLISH_SCHEMES
+                            .map(|scheme| format!("{}://", scheme))
+                            .join(" or "),
+                        target
+                    ),
+                });
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Conversion source
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
+pub struct ConvertSource {
+    pub path: String,
+
+    /// Source format (detected when omitted)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+
+    /// Checksum for verification (see [`ImageSpec::checksum`])
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+}
+
+/// Conversion target
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
+pub struct ConvertTarget {
+    pub path: String,
+
+    /// Target format (one of [`CONVERT_TARGET_FORMATS`])
+    pub format: String,
+
+    #[serde(default)]
+    pub compression: bool,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compression_type: Option<String>,
+}
+
+/// Convert options
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
+pub struct ConvertOptions {
+    #[serde(default = "default_true")]
+    pub verify_after_convert: bool,
+    #[serde(default = "default_true")]
+    pub preserve_sparse: bool,
+    #[serde(default)]
+    pub overwrite: bool,
+
+    /// Publish the converted image to these targets after verification
+    /// (`glance://NAME...` or `proxmox://HOST/NODE/STORAGE...`)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub publish: Vec<String>,
+}
+
+impl Default for ConvertOptions {
+    fn default() -> Self {
+        Self {
+            verify_after_convert: true,
+            preserve_sparse: true,
+            overwrite: false,
+            publish: Vec::new(),
+        }
+    }
+}
+
+// ========================================
+// guestkit.fix
+// ========================================
+
+/// Guestkit fix payload (v1)
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
+pub struct FixPayload {
+    pub image: FixImageSpec,
+
+    /// Fix plan object, or a plan document (YAML or JSON) as a string
+    pub plan: serde_json::Value,
+
+    #[serde(default)]
+    pub options: FixOptions,
+}
+
+impl FixPayload {
+    /// Apply a fix plan to a local image
+    pub fn new(path: impl Into<String>, plan: serde_json::Value) -> Self {
+        Self {
+            image: FixImageSpec {
+                path: path.into(),
+                format: None,
+                checksum: None,
+                create_backup: false,
+                backup_path: None,
+            },
+            plan,
+            options: FixOptions::default(),
+        }
+    }
+
+    /// Copy the image before applying the plan
+    pub fn with_backup(mut self, backup_path: Option<String>) -> Self {
+        self.image.create_backup = true;
+        self.image.backup_path = backup_path;
+        self
+    }
+
+    /// Report what the plan would change without writing to the image
+    pub fn with_dry_run(mut self, dry_run: bool) -> Self {
+        self.options.dry_run = dry_run;
+        self
+    }
+}
+
+impl OperationPayload for FixPayload {
+    const OPERATION: &'static str = operations::GUESTKIT_FIX;
+    const PAYLOAD_TYPE: &'static str = "guestkit.fix.v1";
+
+    fn validate(&self) -> JobResult<()> {
+        require_path("payload.data.image.path", &self.image.path)?;
+
+        // The plan itself is checked by the worker, which owns the plan format
+        if !(self.plan.is_object() || self.plan.is_string()) {
+            return Err(JobError::InvalidField {
+                field: "payload.data.plan".to_string(),
+                reason: "must be a plan object or a plan document string".to_string(),
+            });
+        }
+
+        Ok(())
+    }
+}
+
+/// Image to fix
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
+pub struct FixImageSpec {
+    pub path: String,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub format: Option<String>,
+
+    /// Checksum for verification (see [`ImageSpec::checksum`])
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub checksum: Option<String>,
+
+    /// Copy the whole image before applying the plan
+    #[serde(default)]
+    pub create_backup: bool,
+
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub backup_path: Option<String>,
+}
+
+/// Fix options
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
+pub struct FixOptions {
+    #[serde(default)]
+    pub dry_run: bool,
+
+    /// Fail the job when any operation fails
+    #[serde(default = "default_true")]
+    pub fail_on_error: bool,
+}
+
+impl Default for FixOptions {
+    fn default() -> Self {
+        Self {
+            dry_run: false,
+            fail_on_error: true,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::JobBuilder;
+
+    #[test]
+    fn test_payload_round_trip() {
+        let payload = ConvertPayload::new("/vms/a.vmdk", "/vms/a.qcow2", "qcow2")
+            .with_source_format("vmdk")
+            .with_compression(Some("zstd".to_string()));
+
+        let wire = payload.to_payload().unwrap();
+        assert_eq!(wire.payload_type, "guestkit.convert.v1");
+        assert_eq!(wire.data["target"]["compression_type"], "zstd");
+        assert_eq!(ConvertPayload::from_payload(&wire).unwrap(), payload);
+    }
+
+    #[test]
+    fn test_from_payload_checks_type_and_data() {
+        let wire = InspectPayload::new("/vms/a.qcow2", "qcow2")
+            .to_payload()
+            .unwrap();
+        assert!(matches!(
+            FixPayload::from_payload(&wire),
+            Err(JobError::InvalidField { field, .. }) if field == "payload.type"
+        ));
+
+        let missing_image = Payload {
+            payload_type: "guestkit.inspect.v1".to_string(),
+            data: serde_json::json!({}),
+        };
+        assert!(matches!(
+            InspectPayload::from_payload(&missing_image),
+            Err(JobError::InvalidField { field, .. }) if field == "payload.data"
+        ));
+    }
+
+    #[test]
+    fn test_convert_validation() {
+        let valid = ConvertPayload::new("/vms/a.vmdk", "/vms/a.qcow2", "qcow2");
+        assert!(valid.validate().is_ok());
+
+        let same_path = ConvertPayload::new("/vms/a.qcow2", "/vms/a.qcow2", "qcow2");
+        assert!(same_path.validate().is_err());
+
+        let raw_compressed =
+            ConvertPayload::new("/vms/a.vmdk", "/vms/a.img", "raw").with_compression(None);
+        assert!(matches!(
+            raw_compressed.validate(),
+            Err(JobError::InvalidField { field, .. }) if field == "payload.data.target.compression"
+        ));
+
+        let published = ConvertPayload::new("/vms/a.vmdk", "/vms/a.qcow2", "qcow2")
+            .with_publish("glance://web-01?visibility=shared")
+            .with_publish("proxmox://pve.example.com/pve1/local?vmid=120");
+        assert!(published.validate().is_ok());
+
+        let bad_publish = ConvertPayload::new("/vms/a.vmdk", "/vms/a.qcow2", "qcow2")
+            .with_publish("glance://web-01")
+            .with_publish("s3://bucket/a.qcow2");
+        assert!(matches!(
+            bad_publish.validate(),
+            Err(JobError::InvalidField { field, .. }) if field == "payload.data.options.publish[1]"
+        ));
+    }
+
+    #[test]
+    fn test_fix_validation() {
+        let plan = FixPayload::new("/vms/a.qcow2", serde_json::json!("operations: []"));
+        assert!(plan.validate().is_ok());
+
+        let no_plan = FixPayload::new("/vms/a.qcow2", serde_json::Value::Null);
+        assert!(no_plan.validate().is_err());
+    }
+
+    #[test]
+    fn test_typed_payload_checks_operation() {
+        let mut job = JobBuilder::new()
+            .generate_job_id()
+            .typed_payload(
+                FixPayload::new("/vms/a.qcow2", serde_json::json!({})).with_dry_run(true),
+            )
+            .build()
+            .unwrap();
+        assert_eq!(job.operation, "guestkit.fix");
+        assert!(job.typed_payload::<FixPayload>().unwrap().options.dry_run);
+
+        job.operation = "guestkit.inspect".to_string();
+        assert!(matches!(
+            job.typed_payload::<FixPayload>(),
+            Err(JobError::InvalidField { field, .. }) if field == "operation"
+        ));
+    }
+}
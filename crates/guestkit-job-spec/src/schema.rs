@@ -0,0 +1,122 @@
+//! JSON Schema for the protocol documents
+//!
+//! Lets orchestrators written in other languages validate documents and
+//! generate types from them. Requires the `schema-gen` feature; the
+//! `guestkit-job-spec schema` binary writes the same schemas to files.
+
+use schemars::gen::SchemaSettings;
+use schemars::schema::RootSchema;
+use schemars::JsonSchema;
+
+use crate::types::{JobDocument, JobResult, ProgressEvent};
+use crate::version::ProtocolVersion;
+
+/// Names of the documents that have a schema
+pub const DOCUMENTS: &[&str] = &["job-document", "job-result", "progress-event"];
+
+/// Schema for a job document
+pub fn job_document_schema() -> RootSchema {
+    root_schema::<JobDocument>("job")
+}
+
+/// Schema for a job result
+pub fn job_result_schema() -> RootSchema {
+    root_schema::<JobResult>("job-result")
+}
+
+/// Schema for a progress event
+pub fn progress_event_schema() -> RootSchema {
+    root_schema::<ProgressEvent>("progress-event")
+}
+
+/// Schema for a document by name (one of [`DOCUMENTS`])
+pub fn schema_for_document(name: &str) -> Option<RootSchema> {
+    match name {
+        "job-document" => Some(job_document_schema()),
+        "job-result" => Some(job_result_schema()),
+        "progress-event" => Some(progress_event_schema()),
+        _ => None,
+    }
+}
+
+/// Draft-07 schema with an `$id` naming the protocol version
+fn root_schema<T: JsonSchema>(name: &str) -> RootSchema {
+    let mut schema = SchemaSettings::draft07()
+        .into_generator()
+        .into_root_schema_for::<T>();
+
+    schema.schema.metadata().id = Some(format!(
+        "https://guestkit.dev/schemas/{}-v{}.json",
+        name,
+        ProtocolVersion::CURRENT.major
+    ));
+    schema
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::JobBuilder;
+
+    #[test]
+    fn test_schema_for_document() {
+        for name in DOCUMENTS {
+            let schema = schema_for_document(name).unwrap();
+            assert!(schema.schema.metadata.unwrap().id.is_some());
+        }
+        assert!(schema_for_document("job").is_none());
+    }
+
+    #[test]
+    fn test_job_document_schema() {
+        let schema = serde_json::to_value(job_document_schema()).unwrap();
+
+        assert_eq!(schema["$id"], "https://guestkit.dev/schemas/job-v2.json");
+        assert_eq!(schema["additionalProperties"], false);
+
+        let required: Vec<_> = schema["required"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .map(|v| v.as_str().unwrap())
+            .collect();
+        for field in [
+            "version",
+            "job_id",
+            "created_at",
+            "kind",
+            "operation",
+            "payload",
+        ] {
+            assert!(required.contains(&field), "{} not required", field);
+        }
+
+        // Payload type is renamed on the wire
+        assert!(schema["definitions"]["Payload"]["properties"]["type"].is_object());
+    }
+
+    #[test]
+    fn test_built_job_has_schema_fields() {
+        let schema = serde_json::to_value(job_document_schema()).unwrap();
+        let job = JobBuilder::new()
+            .generate_job_id()
+            .operation("guestkit.inspect")
+            .payload("guestkit.inspect.v1", serde_json::json!({}))
+            .build()
+            .unwrap();
+
+        let properties = schema["properties"].as_object().unwrap();
+        for field in serde_json::to_value(&job)
+            .unwrap()
+            .as_object()
+            .unwrap()
+            .keys()
+        {
+            assert!(
+                properties.contains_key(field),
+                "{} missing from schema",
+                field
+            );
+        }
+    }
+}
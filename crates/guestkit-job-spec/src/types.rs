@@ -1,4 +1,4 @@
-//! Core type definitions for the VM Operations Job Protocol v1
+//! Core type definitions for the VM Operations Job Protocol v2
 
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -6,13 +6,15 @@ use chrono::{DateTime, Utc};
 
 /// Top-level job document (envelope)
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
 #[serde(deny_unknown_fields)]
 pub struct JobDocument {
-    /// Protocol version (always "1.0" for v1)
+    /// JSON schema URL
     #[serde(rename = "$schema")]
     pub schema: Option<String>,
 
-    /// Protocol version
+    /// Protocol version ("2.0"; older documents are upconverted by
+    /// [`JobDocument::parse_any`])
     pub version: String,
 
     /// Job specification
@@ -43,6 +45,10 @@ pub struct JobDocument {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub routing: Option<Routing>,
 
+    /// Jobs that must complete successfully before this one may run
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+
     /// Operation-specific payload
     pub payload: Payload,
 
@@ -57,6 +63,7 @@ pub struct JobDocument {
 
 /// Job metadata (labels, annotations, etc.)
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
 #[serde(default)]
 pub struct JobMetadata {
     /// Human-readable job name
@@ -74,10 +81,15 @@ pub struct JobMetadata {
     /// Arbitrary annotations
     #[serde(skip_serializing_if = "Option::is_none")]
     pub annotations: Option<HashMap<String, String>>,
+
+    /// Fencing token of the lease under which the job runs (set by the worker)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fencing_token: Option<u64>,
 }
 
 /// Execution policy and retry configuration
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
 #[serde(default)]
 pub struct ExecutionPolicy {
     /// Unique key for idempotent execution
@@ -102,6 +114,10 @@ pub struct ExecutionPolicy {
 
     /// Whether job can be cancelled
     pub cancellable: bool,
+
+    /// Recurring schedule; the job becomes a template instantiated at each trigger
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub schedule: Option<JobSchedule>,
 }
 
 impl Default for ExecutionPolicy {
@@ -114,12 +130,60 @@ impl Default for ExecutionPolicy {
             deadline: None,
             priority: 5,
             cancellable: true,
+            schedule: None,
+        }
+    }
+}
+
+/// Recurring job schedule
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
+pub struct JobSchedule {
+    /// Cron expression (`"0 2 * * *"`, `"@daily"`) or RRULE (`"RRULE:FREQ=WEEKLY;BYDAY=MO"`)
+    pub expression: String,
+
+    /// Random delay of up to this many seconds added to each trigger
+    #[serde(default)]
+    pub jitter_seconds: u64,
+
+    /// What to do when the previous run is still active
+    #[serde(default)]
+    pub overlap: OverlapPolicy,
+}
+
+impl JobSchedule {
+    /// Create a schedule with no jitter and the default overlap policy
+    pub fn new(expression: impl Into<String>) -> Self {
+        Self {
+            expression: expression.into(),
+            jitter_seconds: 0,
+            overlap: OverlapPolicy::default(),
         }
     }
+
+    /// Check if the expression is an RRULE rather than a cron expression
+    pub fn is_rrule(&self) -> bool {
+        self.expression.contains("FREQ=")
+    }
+}
+
+/// Policy for a schedule trigger while the previous run is still active
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
+#[serde(rename_all = "lowercase")]
+pub enum OverlapPolicy {
+    /// Skip the new run
+    #[default]
+    Forbid,
+    /// Start the new run alongside the previous one
+    Allow,
+    /// Cancel the previous run and start the new one
+    Replace,
 }
 
 /// Capability and resource constraints
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
 #[serde(default)]
 pub struct Constraints {
     /// Required worker capabilities (e.g., ["guestkit.inspect", "disk.qcow2"])
@@ -138,6 +202,14 @@ pub struct Constraints {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub maximum_disk_size_gb: Option<u64>,
 
+    /// Minimum CPU cores the worker must have
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_cpu_cores: Option<u32>,
+
+    /// Minimum memory the worker must have (GB)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub min_memory_gb: Option<u64>,
+
     /// Requires privileged execution
     #[serde(skip_serializing_if = "Option::is_none")]
     pub require_privileged: Option<bool>,
@@ -145,10 +217,20 @@ pub struct Constraints {
     /// Allowed worker pool names
     #[serde(skip_serializing_if = "Option::is_none")]
     pub allowed_worker_pools: Option<Vec<String>>,
+
+    /// Disk bandwidth the job may use in megabytes (10^6 bytes) per second,
+    /// to spare guests on shared storage
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub max_io_bandwidth_mb_per_sec: Option<u64>,
+
+    /// I/O scheduling class of the job: "idle", "best-effort" or "best-effort:0-7"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub io_priority: Option<String>,
 }
 
 /// Routing and scheduling hints
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
 #[serde(default)]
 pub struct Routing {
     /// Pin to specific worker (use sparingly)
@@ -159,9 +241,9 @@ pub struct Routing {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub worker_pool: Option<String>,
 
-    /// Scheduling preferences
+    /// Scheduling preferences; a worker matches a key if it has any of the values
     #[serde(skip_serializing_if = "Option::is_none")]
-    pub affinity: Option<HashMap<String, String>>,
+    pub affinity: Option<HashMap<String, Vec<String>>>,
 
     /// Scheduling anti-preferences
     #[serde(skip_serializing_if = "Option::is_none")]
@@ -170,6 +252,7 @@ pub struct Routing {
 
 /// Operation-specific payload
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
 pub struct Payload {
     /// Payload type (namespace.operation.version)
     #[serde(rename = "type")]
@@ -181,6 +264,7 @@ pub struct Payload {
 
 /// Observability metadata (tracing, correlation)
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
 #[serde(default)]
 pub struct Observability {
     /// Distributed trace ID
@@ -202,6 +286,7 @@ pub struct Observability {
 
 /// Audit trail
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
 #[serde(default)]
 pub struct Audit {
     /// Submitter identity
@@ -215,10 +300,29 @@ pub struct Audit {
     /// Authorization details
     #[serde(skip_serializing_if = "Option::is_none")]
     pub authorization: Option<Authorization>,
+
+    /// Signature over the rest of the document
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub signature: Option<JobSignature>,
+}
+
+/// Detached JWS (RFC 7515 flattened JSON serialization) over a job document
+///
+/// The payload is the canonical form of the job, see
+/// [`crate::signature::signing_input`], and is not repeated here.
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
+pub struct JobSignature {
+    /// Base64url-encoded protected header (`alg`, `kid`)
+    pub protected: String,
+
+    /// Base64url-encoded signature
+    pub signature: String,
 }
 
 /// Authorization details
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
 pub struct Authorization {
     /// Authorization method
     pub method: String,
@@ -233,6 +337,7 @@ pub struct Authorization {
 
 /// Worker capability advertisement
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
 pub struct WorkerCapabilities {
     /// Worker ID
     pub worker_id: String,
@@ -261,6 +366,7 @@ pub struct WorkerCapabilities {
 
 /// Worker capability set
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
 #[serde(default)]
 pub struct WorkerCapabilitySet {
     /// Supported operations
@@ -271,10 +377,14 @@ pub struct WorkerCapabilitySet {
 
     /// Supported disk formats
     pub disk_formats: Vec<String>,
+
+    /// Job protocol versions the worker accepts (e.g., ["1.0", "2.0"])
+    pub protocol_versions: Vec<String>,
 }
 
 /// Worker resource information
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
 pub struct WorkerResources {
     /// Maximum concurrent jobs
     pub max_concurrent_jobs: u32,
@@ -294,6 +404,7 @@ pub struct WorkerResources {
 
 /// Worker configuration
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
 pub struct WorkerConfiguration {
     /// Whether worker runs privileged
     pub privileged: bool,
@@ -309,6 +420,7 @@ pub struct WorkerConfiguration {
 
 /// Worker status
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
 pub struct WorkerStatus {
     /// Worker state
     pub state: WorkerState,
@@ -322,6 +434,7 @@ pub struct WorkerStatus {
 
 /// Worker state
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
 #[serde(rename_all = "lowercase")]
 pub enum WorkerState {
     Ready,
@@ -336,6 +449,7 @@ pub enum WorkerState {
 
 /// Job execution result
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
 pub struct JobResult {
     /// Job ID
     pub job_id: String,
@@ -376,6 +490,7 @@ pub struct JobResult {
 
 /// Job status
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
 #[serde(rename_all = "lowercase")]
 pub enum JobStatus {
     Pending,
@@ -389,6 +504,7 @@ pub enum JobStatus {
 
 /// Execution summary
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
 pub struct ExecutionSummary {
     /// Start timestamp
     pub started_at: DateTime<Utc>,
@@ -406,6 +522,7 @@ pub struct ExecutionSummary {
 
 /// Job outputs
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
 #[serde(default)]
 pub struct JobOutputs {
     /// Primary output file
@@ -419,6 +536,7 @@ pub struct JobOutputs {
 
 /// Execution metrics
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
 #[serde(default)]
 pub struct ExecutionMetrics {
     /// Disk bytes read
@@ -436,10 +554,19 @@ pub struct ExecutionMetrics {
     /// CPU seconds consumed
     #[serde(skip_serializing_if = "Option::is_none")]
     pub cpu_seconds: Option<u64>,
+
+    /// Seconds the job waited for an execution slot
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub queue_wait_seconds: Option<f64>,
+
+    /// Seconds the job ran once it had a slot
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub run_seconds: Option<f64>,
 }
 
 /// Job execution error
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
 pub struct JobExecutionError {
     /// Error code
     pub code: String,
@@ -468,6 +595,7 @@ pub struct JobExecutionError {
 
 /// Progress event emitted during job execution
 #[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[cfg_attr(feature = "schema-gen", derive(schemars::JsonSchema))]
 pub struct ProgressEvent {
     /// Job ID
     pub job_id: String,
@@ -501,81 +629,12 @@ pub struct ProgressEvent {
 // Guestkit-specific payload types
 // ========================================
 
-/// Guestkit inspect payload (v1)
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub struct GuestkitInspectPayload {
-    pub image: ImageSpec,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub options: Option<InspectOptions>,
-
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub output: Option<OutputSpec>,
-}
-
-/// Image specification
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub struct ImageSpec {
-    /// Path to image file
-    pub path: String,
-
-    /// Image format (qcow2, vmdk, etc.)
-    pub format: String,
-
-    /// Checksum for verification (SHA256)
-    ///
-    /// Supports two formats:
-    /// - `"sha256:hexhash"` - Explicit algorithm specification
-    /// - `"hexhash"` - Defaults to SHA256
-    ///
-    /// Example: `"sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"`
-    ///
-    /// When provided, the worker will compute the SHA256 hash of the image file
-    /// and verify it matches the expected value before processing. This protects
-    /// against corrupted or tampered images.
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub checksum: Option<String>,
-
-    /// Image size in bytes
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub size_bytes: Option<u64>,
-
-    /// Whether to open read-only
-    #[serde(default = "default_true")]
-    pub read_only: bool,
-}
+// Typed payloads live in `crate::payloads`; re-exported here so existing
+// imports keep working.
+pub use crate::payloads::{ImageSpec, InspectOptions, OutputSpec};
 
-fn default_true() -> bool {
-    true
-}
-
-/// Inspect options
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
-#[serde(default)]
-pub struct InspectOptions {
-    pub deep_scan: bool,
-    pub include_packages: bool,
-    pub include_services: bool,
-    pub include_users: bool,
-    pub include_network: bool,
-    pub include_security: bool,
-    pub include_storage: bool,
-    pub include_databases: bool,
-}
-
-/// Output specification
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
-pub struct OutputSpec {
-    /// Output format (json, yaml, etc.)
-    pub format: String,
-
-    /// Destination path
-    pub destination: String,
-
-    /// Optional compression
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub compression: Option<String>,
-}
+/// Guestkit inspect payload (v1)
+pub type GuestkitInspectPayload = crate::payloads::InspectPayload;
 
 // ========================================
 // Convenience types
@@ -591,7 +650,7 @@ mod tests {
     #[test]
     fn test_job_document_serialization() {
         let job = JobDocument {
-            schema: Some("https://guestkit.dev/schemas/job-v1.json".to_string()),
+            schema: Some("https://guestkit.dev/schemas/job-v2.json".to_string()),
             version: "1.0".to_string(),
             job_id: "job-test-123".to_string(),
             created_at: Utc::now(),
@@ -601,6 +660,7 @@ mod tests {
             execution: None,
             constraints: None,
             routing: None,
+            depends_on: vec!["job-inspect-123".to_string()],
             payload: Payload {
                 payload_type: "guestkit.inspect.v1".to_string(),
                 data: serde_json::json!({"test": "data"}),
@@ -623,5 +683,19 @@ mod tests {
         assert_eq!(policy.timeout_seconds, 3600);
         assert_eq!(policy.priority, 5);
         assert!(policy.cancellable);
+        assert!(policy.schedule.is_none());
+    }
+
+    #[test]
+    fn test_schedule_deserialization() {
+        let policy: ExecutionPolicy = serde_json::from_value(serde_json::json!({
+            "schedule": { "expression": "0 2 * * *", "overlap": "replace" }
+        }))
+        .unwrap();
+
+        let schedule = policy.schedule.unwrap();
+        assert_eq!(schedule.jitter_seconds, 0);
+        assert_eq!(schedule.overlap, OverlapPolicy::Replace);
+        assert!(!schedule.is_rrule());
     }
 }
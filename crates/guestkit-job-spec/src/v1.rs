@@ -0,0 +1,89 @@
+//! Wire types of protocol v1
+//!
+//! Only the structures that changed in v2 are kept here; everything else is
+//! shared with [`crate::types`]. Use [`crate::version::migrate_v1_to_v2`] to
+//! upconvert.
+
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use chrono::{DateTime, Utc};
+
+use crate::types::{
+    Audit, Constraints, ExecutionPolicy, JobMetadata, Observability, Payload,
+};
+
+/// Protocol v1 job document
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+#[serde(deny_unknown_fields)]
+pub struct JobDocument {
+    /// JSON schema URL
+    #[serde(rename = "$schema")]
+    pub schema: Option<String>,
+
+    /// Protocol version ("1.x")
+    pub version: String,
+
+    /// Job specification
+    pub job_id: String,
+
+    /// Timestamp when job was created
+    pub created_at: DateTime<Utc>,
+
+    /// Job kind (always "VMOperation")
+    pub kind: String,
+
+    /// Namespaced operation name
+    pub operation: String,
+
+    /// Job metadata
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub metadata: Option<JobMetadata>,
+
+    /// Execution policy
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub execution: Option<ExecutionPolicy>,
+
+    /// Capability and resource constraints
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub constraints: Option<Constraints>,
+
+    /// Routing and scheduling hints
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub routing: Option<Routing>,
+
+    /// Jobs that must complete successfully before this one may run
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub depends_on: Vec<String>,
+
+    /// Operation-specific payload
+    pub payload: Payload,
+
+    /// Observability metadata
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub observability: Option<Observability>,
+
+    /// Audit trail
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub audit: Option<Audit>,
+}
+
+/// Protocol v1 routing hints: one preferred value per affinity key
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+#[serde(default)]
+pub struct Routing {
+    /// Pin to specific worker (use sparingly)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub worker_id: Option<String>,
+
+    /// Target worker pool
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub worker_pool: Option<String>,
+
+    /// Scheduling preferences
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub affinity: Option<HashMap<String, String>>,
+
+    /// Scheduling anti-preferences
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub anti_affinity: Option<HashMap<String, Vec<String>>>,
+}
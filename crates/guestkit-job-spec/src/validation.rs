@@ -1,8 +1,9 @@
 //! Job validation logic
 
 use crate::error::{JobError, JobResult};
+use crate::signature::SignaturePolicy;
 use crate::types::{JobDocument, Payload};
-use crate::PROTOCOL_VERSION;
+use crate::version::ProtocolVersion;
 
 /// Job validator
 pub struct JobValidator;
@@ -24,6 +25,7 @@ impl JobValidator {
 
         // Validate payload
         Self::validate_payload(&job.payload)?;
+        Self::validate_payload_operation(&job.operation, &job.payload)?;
 
         // Validate execution policy if present
         if let Some(ref execution) = job.execution {
@@ -35,12 +37,50 @@ impl JobValidator {
             Self::validate_constraints(constraints)?;
         }
 
+        // Validate dependencies
+        Self::validate_dependencies(job)?;
+
+        Ok(())
+    }
+
+    /// Validate a set of related jobs, including their dependency graph
+    pub fn validate_graph(jobs: &[JobDocument]) -> JobResult<()> {
+        for job in jobs {
+            Self::validate(job)?;
+        }
+
+        crate::graph::topological_order(jobs)?;
+
+        Ok(())
+    }
+
+    /// Verify a job's signature against the policy's trust anchors
+    ///
+    /// A signed job must verify against a trusted key. Unsigned jobs are
+    /// only accepted when the policy does not require signatures.
+    pub fn verify_signature(job: &JobDocument, policy: &SignaturePolicy) -> JobResult<()> {
+        let signed = job
+            .audit
+            .as_ref()
+            .is_some_and(|audit| audit.signature.is_some());
+
+        if !signed {
+            if policy.require_signature {
+                return Err(JobError::UnsignedJob);
+            }
+            return Ok(());
+        }
+
+        crate::signature::verify(job, &policy.anchors)?;
         Ok(())
     }
 
     /// Validate protocol version
+    ///
+    /// Documents of older versions must be upconverted with
+    /// [`JobDocument::parse_any`] first.
     fn validate_version(version: &str) -> JobResult<()> {
-        if version != PROTOCOL_VERSION {
+        if !ProtocolVersion::CURRENT.accepts(&ProtocolVersion::parse(version)?) {
             return Err(JobError::UnsupportedVersion(version.to_string()));
         }
         Ok(())
@@ -118,6 +158,55 @@ impl JobValidator {
         Ok(())
     }
 
+    /// Validate that the payload type belongs to the declared operation
+    ///
+    /// A `guestkit.inspect` job must carry a `guestkit.inspect.vN` payload.
+    fn validate_payload_operation(operation: &str, payload: &Payload) -> JobResult<()> {
+        let payload_operation = payload
+            .payload_type
+            .rsplit_once('.')
+            .map_or(payload.payload_type.as_str(), |(operation, _)| operation);
+
+        if payload_operation != operation {
+            return Err(JobError::InvalidField {
+                field: "payload.type".to_string(),
+                reason: format!(
+                    "'{}' does not match operation '{}'",
+                    payload.payload_type, operation
+                ),
+            });
+        }
+
+        Ok(())
+    }
+
+    /// Validate dependency references
+    fn validate_dependencies(job: &JobDocument) -> JobResult<()> {
+        let mut seen = std::collections::HashSet::new();
+
+        for dep in &job.depends_on {
+            if dep.is_empty() {
+                return Err(JobError::InvalidField {
+                    field: "depends_on".to_string(),
+                    reason: "job IDs cannot be empty".to_string(),
+                });
+            }
+
+            if *dep == job.job_id {
+                return Err(JobError::DependencyCycle(vec![dep.clone(), dep.clone()]));
+            }
+
+            if !seen.insert(dep.as_str()) {
+                return Err(JobError::InvalidField {
+                    field: "depends_on".to_string(),
+                    reason: format!("duplicate dependency '{}'", dep),
+                });
+            }
+        }
+
+        Ok(())
+    }
+
     /// Validate execution policy
     fn validate_execution_policy(policy: &crate::types::ExecutionPolicy) -> JobResult<()> {
         // Priority must be 1-10
@@ -147,6 +236,10 @@ impl JobValidator {
             });
         }
 
+        if let Some(ref schedule) = policy.schedule {
+            Self::validate_schedule(schedule)?;
+        }
+
         // Timeout should be reasonable (warn if > 24 hours)
         const MAX_REASONABLE_TIMEOUT: u64 = 86400; // 24 hours
         if policy.timeout_seconds > MAX_REASONABLE_TIMEOUT {
@@ -159,6 +252,45 @@ impl JobValidator {
         Ok(())
     }
 
+    /// Validate schedule expression shape
+    ///
+    /// Full parsing happens in the worker scheduler; this only rejects
+    /// expressions that are neither cron nor a supported RRULE.
+    fn validate_schedule(schedule: &crate::types::JobSchedule) -> JobResult<()> {
+        let expression = schedule.expression.trim();
+
+        if expression.is_empty() {
+            return Err(JobError::MissingField("execution.schedule.expression".to_string()));
+        }
+
+        let valid = if schedule.is_rrule() {
+            expression
+                .split(|c: char| c == ';' || c == ':' || c.is_whitespace())
+                .any(|part| {
+                    matches!(
+                        part.strip_prefix("FREQ="),
+                        Some("MINUTELY" | "HOURLY" | "DAILY" | "WEEKLY" | "MONTHLY")
+                    )
+                })
+        } else if expression.starts_with('@') {
+            matches!(
+                expression,
+                "@yearly" | "@annually" | "@monthly" | "@weekly" | "@daily" | "@hourly"
+            )
+        } else {
+            (5..=7).contains(&expression.split_whitespace().count())
+        };
+
+        if !valid {
+            return Err(JobError::InvalidField {
+                field: "execution.schedule.expression".to_string(),
+                reason: format!("'{}' is not a cron expression or supported RRULE", expression),
+            });
+        }
+
+        Ok(())
+    }
+
     /// Validate constraints
     fn validate_constraints(constraints: &crate::types::Constraints) -> JobResult<()> {
         // Validate semver if minimum_worker_version is provided
@@ -182,6 +314,13 @@ impl JobValidator {
             }
         }
 
+        if constraints.min_cpu_cores == Some(0) {
+            return Err(JobError::InvalidField {
+                field: "constraints.min_cpu_cores".to_string(),
+                reason: "must be at least 1".to_string(),
+            });
+        }
+
         Ok(())
     }
 
@@ -216,7 +355,7 @@ mod tests {
     fn create_minimal_valid_job() -> JobDocument {
         JobDocument {
             schema: None,
-            version: "1.0".to_string(),
+            version: "2.0".to_string(),
             job_id: "job-12345678".to_string(),
             created_at: Utc::now(),
             kind: "VMOperation".to_string(),
@@ -225,6 +364,7 @@ mod tests {
             execution: None,
             constraints: None,
             routing: None,
+            depends_on: Vec::new(),
             payload: Payload {
                 payload_type: "guestkit.inspect.v1".to_string(),
                 data: serde_json::json!({}),
@@ -243,10 +383,14 @@ mod tests {
     #[test]
     fn test_validate_invalid_version() {
         let mut job = create_minimal_valid_job();
-        job.version = "2.0".to_string();
+        job.version = "3.0".to_string();
 
         let result = JobValidator::validate(&job);
         assert!(matches!(result, Err(JobError::UnsupportedVersion(_))));
+
+        // v1 documents must be upconverted before validation
+        job.version = "1.0".to_string();
+        assert!(JobValidator::validate(&job).is_err());
     }
 
     #[test]
@@ -285,6 +429,90 @@ mod tests {
         assert!(matches!(result, Err(JobError::InvalidField { .. })));
     }
 
+    #[test]
+    fn test_validate_payload_operation_mismatch() {
+        let mut job = create_minimal_valid_job();
+        job.payload.payload_type = "guestkit.fix.v1".to_string();
+
+        let result = JobValidator::validate(&job);
+        assert!(matches!(result, Err(JobError::InvalidField { field, .. }) if field == "payload.type"));
+    }
+
+    #[test]
+    fn test_validate_self_dependency() {
+        let mut job = create_minimal_valid_job();
+        job.depends_on = vec![job.job_id.clone()];
+
+        let result = JobValidator::validate(&job);
+        assert!(matches!(result, Err(JobError::DependencyCycle(_))));
+    }
+
+    #[test]
+    fn test_validate_graph_cycle() {
+        let mut inspect = create_minimal_valid_job();
+        inspect.job_id = "job-inspect-1".to_string();
+        inspect.depends_on = vec!["job-convert-1".to_string()];
+
+        let mut convert = create_minimal_valid_job();
+        convert.job_id = "job-convert-1".to_string();
+        convert.operation = "guestkit.convert".to_string();
+        convert.payload.payload_type = "guestkit.convert.v1".to_string();
+        convert.depends_on = vec!["job-inspect-1".to_string()];
+
+        let result = JobValidator::validate_graph(&[inspect.clone(), convert.clone()]);
+        assert!(matches!(result, Err(JobError::DependencyCycle(_))));
+
+        inspect.depends_on.clear();
+        assert!(JobValidator::validate_graph(&[convert, inspect]).is_ok());
+    }
+
+    #[test]
+    fn test_validate_schedule() {
+        let mut job = create_minimal_valid_job();
+        let mut policy = crate::types::ExecutionPolicy::default();
+
+        for expression in ["0 2 * * *", "@daily", "RRULE:FREQ=WEEKLY;BYDAY=MO,TH"] {
+            policy.schedule = Some(crate::types::JobSchedule::new(expression));
+            job.execution = Some(policy.clone());
+            assert!(JobValidator::validate(&job).is_ok(), "{}", expression);
+        }
+
+        for expression in ["", "every day", "@sometimes", "RRULE:FREQ=SECONDLY"] {
+            policy.schedule = Some(crate::types::JobSchedule::new(expression));
+            job.execution = Some(policy.clone());
+            assert!(JobValidator::validate(&job).is_err(), "{}", expression);
+        }
+    }
+
+    #[test]
+    fn test_verify_signature_policy() {
+        use crate::signature::{generate_ed25519_pkcs8, JobSigner, TrustAnchors};
+
+        let signer = JobSigner::from_pkcs8_der("ops", &generate_ed25519_pkcs8().unwrap()).unwrap();
+        let mut anchors = TrustAnchors::new();
+        anchors.insert("ops", signer.public_key());
+
+        let lenient = SignaturePolicy::new(anchors.clone());
+        let strict = SignaturePolicy::new(anchors).strict();
+
+        let mut job = create_minimal_valid_job();
+        assert!(JobValidator::verify_signature(&job, &lenient).is_ok());
+        assert!(matches!(
+            JobValidator::verify_signature(&job, &strict),
+            Err(JobError::UnsignedJob)
+        ));
+
+        signer.sign(&mut job).unwrap();
+        assert!(JobValidator::verify_signature(&job, &strict).is_ok());
+
+        // Tampered jobs are rejected in either mode
+        job.operation = "guestkit.fix".to_string();
+        assert!(matches!(
+            JobValidator::verify_signature(&job, &lenient),
+            Err(JobError::InvalidSignature(_))
+        ));
+    }
+
     #[test]
     fn test_check_capabilities_match() {
         let required = vec!["lvm".to_string(), "nbd".to_string()];
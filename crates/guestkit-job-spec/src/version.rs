@@ -0,0 +1,366 @@
+//! Protocol version negotiation and migration
+//!
+//! Documents declare their protocol version in `version`. Readers parse the
+//! declared version, deserialize the matching wire types and upconvert them
+//! to the current model with explicit migration functions, so producers and
+//! workers can move to a new protocol version independently. Writers that
+//! talk to an older peer convert back with [`JobDocument::to_version`].
+//!
+//! Changes in v2:
+//! - `routing.affinity` maps each key to a list of accepted values instead
+//!   of a single value.
+
+use std::fmt;
+use std::str::FromStr;
+
+use serde_json::Value;
+
+use crate::error::{JobError, JobResult};
+use crate::types::{JobDocument, Routing};
+use crate::v1;
+
+/// Protocol version (`major.minor`)
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct ProtocolVersion {
+    pub major: u32,
+    pub minor: u32,
+}
+
+impl ProtocolVersion {
+    /// Protocol v1.0
+    pub const V1: Self = Self::new(1, 0);
+
+    /// Protocol v2.0
+    pub const V2: Self = Self::new(2, 0);
+
+    /// Version of the types in [`crate::types`]
+    pub const CURRENT: Self = Self::V2;
+
+    /// Versions this crate can read and write
+    pub const SUPPORTED: &'static [Self] = &[Self::V1, Self::V2];
+
+    pub const fn new(major: u32, minor: u32) -> Self {
+        Self { major, minor }
+    }
+
+    /// Parse a version such as "1.0" (a bare major version means minor 0)
+    pub fn parse(version: &str) -> JobResult<Self> {
+        let invalid = || JobError::UnsupportedVersion(version.to_string());
+        let (major, minor) = version.split_once('.').unwrap_or((version, "0"));
+        Ok(Self {
+            major: major.parse().map_err(|_| invalid())?,
+            minor: minor.parse().map_err(|_| invalid())?,
+        })
+    }
+
+    /// Check if a reader of this version understands `other`
+    ///
+    /// Minor versions only add fields, so a reader accepts the same major
+    /// version up to its own minor version.
+    pub fn accepts(&self, other: &Self) -> bool {
+        self.major == other.major && other.minor <= self.minor
+    }
+
+    /// Check if this crate can read documents of this version
+    pub fn is_supported(&self) -> bool {
+        Self::SUPPORTED.iter().any(|v| v.accepts(self))
+    }
+}
+
+impl fmt::Display for ProtocolVersion {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}.{}", self.major, self.minor)
+    }
+}
+
+impl FromStr for ProtocolVersion {
+    type Err = JobError;
+
+    fn from_str(s: &str) -> JobResult<Self> {
+        Self::parse(s)
+    }
+}
+
+/// Pick the version to talk in, given what each side supports
+///
+/// Returns the highest major version both sides support, at the lower of
+/// the two sides' minor versions, or `None` if they have no major version in
+/// common.
+pub fn negotiate(ours: &[ProtocolVersion], theirs: &[ProtocolVersion]) -> Option<ProtocolVersion> {
+    let highest_minor = |versions: &[ProtocolVersion], major: u32| {
+        versions
+            .iter()
+            .filter(|v| v.major == major)
+            .map(|v| v.minor)
+            .max()
+    };
+
+    ours.iter()
+        .filter_map(|v| {
+            let minor = highest_minor(ours, v.major)?.min(highest_minor(theirs, v.major)?);
+            Some(ProtocolVersion::new(v.major, minor))
+        })
+        .max()
+}
+
+/// Upconvert a v1 document to the current model
+pub fn migrate_v1_to_v2(job: v1::JobDocument) -> JobDocument {
+    JobDocument {
+        schema: job.schema,
+        version: ProtocolVersion::V2.to_string(),
+        job_id: job.job_id,
+        created_at: job.created_at,
+        kind: job.kind,
+        operation: job.operation,
+        metadata: job.metadata,
+        execution: job.execution,
+        constraints: job.constraints,
+        routing: job.routing.map(|routing| Routing {
+            worker_id: routing.worker_id,
+            worker_pool: routing.worker_pool,
+            affinity: routing.affinity.map(|affinity| {
+                affinity
+                    .into_iter()
+                    .map(|(key, value)| (key, vec![value]))
+                    .collect()
+            }),
+            anti_affinity: routing.anti_affinity,
+        }),
+        depends_on: job.depends_on,
+        payload: job.payload,
+        observability: job.observability,
+        audit: job.audit,
+    }
+}
+
+/// Convert a current document to v1 wire types for an older peer
+///
+/// Fails if the document uses something v1 cannot express, such as an
+/// affinity key with several values.
+pub fn downgrade_v2_to_v1(job: &JobDocument) -> JobResult<v1::JobDocument> {
+    let routing = match &job.routing {
+        Some(routing) => {
+            let affinity = match &routing.affinity {
+                Some(affinity) => {
+                    let mut single = std::collections::HashMap::new();
+                    for (key, values) in affinity {
+                        let [value] = values.as_slice() else {
+                            return Err(JobError::InvalidField {
+                                field: format!("routing.affinity.{}", key),
+                                reason: format!(
+                                    "protocol v1 allows exactly one value, got {}",
+                                    values.len()
+                                ),
+                            });
+                        };
+                        single.insert(key.clone(), value.clone());
+                    }
+                    Some(single)
+                }
+                None => None,
+            };
+            Some(v1::Routing {
+                worker_id: routing.worker_id.clone(),
+                worker_pool: routing.worker_pool.clone(),
+                affinity,
+                anti_affinity: routing.anti_affinity.clone(),
+            })
+        }
+        None => None,
+    };
+
+    Ok(v1::JobDocument {
+        schema: job.schema.clone(),
+        version: ProtocolVersion::V1.to_string(),
+        job_id: job.job_id.clone(),
+        created_at: job.created_at,
+        kind: job.kind.clone(),
+        operation: job.operation.clone(),
+        metadata: job.metadata.clone(),
+        execution: job.execution.clone(),
+        constraints: job.constraints.clone(),
+        routing,
+        depends_on: job.depends_on.clone(),
+        payload: job.payload.clone(),
+        observability: job.observability.clone(),
+        audit: job.audit.clone(),
+    })
+}
+
+impl JobDocument {
+    /// Parse a JSON job document of any supported protocol version
+    ///
+    /// Older documents are upconverted to the current model.
+    pub fn parse_any(json: &str) -> JobResult<Self> {
+        Self::from_value_any(serde_json::from_str(json)?)
+    }
+
+    /// Like [`JobDocument::parse_any`], for an already parsed document
+    /// (e.g., one read from YAML)
+    pub fn from_value_any(value: Value) -> JobResult<Self> {
+        let declared = value
+            .get("version")
+            .and_then(Value::as_str)
+            .ok_or_else(|| JobError::MissingField("version".to_string()))?;
+        let version = ProtocolVersion::parse(declared)?;
+
+        if ProtocolVersion::V1.accepts(&version) {
+            let job: v1::JobDocument = serde_json::from_value(value)?;
+            Ok(migrate_v1_to_v2(job))
+        } else if ProtocolVersion::V2.accepts(&version) {
+            Ok(serde_json::from_value(value)?)
+        } else {
+            Err(JobError::UnsupportedVersion(declared.to_string()))
+        }
+    }
+
+    /// Serialize the document in the wire format of `version`
+    pub fn to_version(&self, version: ProtocolVersion) -> JobResult<Value> {
+        if ProtocolVersion::V1.accepts(&version) {
+            Ok(serde_json::to_value(downgrade_v2_to_v1(self)?)?)
+        } else if ProtocolVersion::V2.accepts(&version) {
+            Ok(serde_json::to_value(self)?)
+        } else {
+            Err(JobError::UnsupportedVersion(version.to_string()))
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::JobBuilder;
+
+    fn v1_json() -> Value {
+        serde_json::json!({
+            "version": "1.0",
+            "job_id": "job-01HQZX",
+            "created_at": "2026-01-15T10:30:00Z",
+            "kind": "VMOperation",
+            "operation": "guestkit.inspect",
+            "routing": {
+                "worker_pool": "pool-a",
+                "affinity": { "zone": "us-east-1a" }
+            },
+            "payload": {
+                "type": "guestkit.inspect.v1",
+                "data": { "image": { "path": "/vms/a.qcow2" } }
+            }
+        })
+    }
+
+    #[test]
+    fn test_parse_version() {
+        assert_eq!(ProtocolVersion::parse("1.0").unwrap(), ProtocolVersion::V1);
+        assert_eq!(ProtocolVersion::parse("2").unwrap(), ProtocolVersion::V2);
+        assert_eq!("2.3".parse::<ProtocolVersion>().unwrap().to_string(), "2.3");
+        assert!(matches!(
+            ProtocolVersion::parse("v2"),
+            Err(JobError::UnsupportedVersion(_))
+        ));
+
+        assert!(ProtocolVersion::V1.is_supported());
+        assert!(!ProtocolVersion::new(2, 1).is_supported());
+        assert!(!ProtocolVersion::new(3, 0).is_supported());
+    }
+
+    #[test]
+    fn test_negotiate() {
+        let ours = ProtocolVersion::SUPPORTED;
+
+        assert_eq!(
+            negotiate(ours, &[ProtocolVersion::V1]),
+            Some(ProtocolVersion::V1)
+        );
+        assert_eq!(
+            negotiate(ours, &[ProtocolVersion::V1, ProtocolVersion::new(2, 4)]),
+            Some(ProtocolVersion::V2)
+        );
+        assert_eq!(negotiate(ours, &[ProtocolVersion::new(3, 0)]), None);
+        assert_eq!(negotiate(ours, &[]), None);
+    }
+
+    #[test]
+    fn test_parse_any_upconverts_v1() {
+        let job = JobDocument::parse_any(&v1_json().to_string()).unwrap();
+
+        assert_eq!(job.version, "2.0");
+        let routing = job.routing.unwrap();
+        assert_eq!(routing.worker_pool.as_deref(), Some("pool-a"));
+        assert_eq!(
+            routing.affinity.unwrap()["zone"],
+            vec!["us-east-1a".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_parse_any_reads_v2() {
+        let mut value = v1_json();
+        value["version"] = "2.0".into();
+        value["routing"]["affinity"]["zone"] = serde_json::json!(["us-east-1a", "us-east-1b"]);
+
+        let job = JobDocument::from_value_any(value).unwrap();
+        assert_eq!(job.routing.unwrap().affinity.unwrap()["zone"].len(), 2);
+
+        // A v1 document must not use v2 structures
+        let mut value = v1_json();
+        value["routing"]["affinity"]["zone"] = serde_json::json!(["us-east-1a"]);
+        assert!(JobDocument::from_value_any(value).is_err());
+    }
+
+    #[test]
+    fn test_parse_any_rejects_unknown_versions() {
+        let mut value = v1_json();
+        value["version"] = "3.0".into();
+        assert!(matches!(
+            JobDocument::from_value_any(value),
+            Err(JobError::UnsupportedVersion(v)) if v == "3.0"
+        ));
+
+        let mut value = v1_json();
+        value.as_object_mut().unwrap().remove("version");
+        assert!(matches!(
+            JobDocument::from_value_any(value),
+            Err(JobError::MissingField(_))
+        ));
+    }
+
+    #[test]
+    fn test_to_version_round_trip() {
+        let job = JobDocument::from_value_any(v1_json()).unwrap();
+
+        let v1 = job.to_version(ProtocolVersion::V1).unwrap();
+        assert_eq!(v1["version"], "1.0");
+        assert_eq!(v1["routing"]["affinity"]["zone"], "us-east-1a");
+        assert_eq!(JobDocument::from_value_any(v1).unwrap(), job);
+
+        assert_eq!(
+            job.to_version(ProtocolVersion::V2).unwrap()["version"],
+            "2.0"
+        );
+        assert!(job.to_version(ProtocolVersion::new(3, 0)).is_err());
+    }
+
+    #[test]
+    fn test_downgrade_rejects_multi_valued_affinity() {
+        let mut job = JobBuilder::new()
+            .generate_job_id()
+            .operation("guestkit.inspect")
+            .payload("guestkit.inspect.v1", serde_json::json!({}))
+            .build()
+            .unwrap();
+        job.routing = Some(Routing {
+            affinity: Some(
+                [("zone".to_string(), vec!["a".to_string(), "b".to_string()])]
+                    .into_iter()
+                    .collect(),
+            ),
+            ..Default::default()
+        });
+
+        assert!(matches!(
+            downgrade_v2_to_v1(&job),
+            Err(JobError::InvalidField { field, .. }) if field == "routing.affinity.zone"
+        ));
+    }
+}
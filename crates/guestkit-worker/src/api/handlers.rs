@@ -2,17 +2,25 @@
 
 use axum::{
     extract::{Path, State},
+    response::sse::{Event, KeepAlive, Sse},
     Json,
 };
 use chrono::Utc;
-use guestkit_job_spec::{JobDocument, JobValidator, JobStatus};
+use guestkit_job_spec::{JobDocument, JobValidator, JobStatus, ProtocolVersion};
+use serde::Serialize;
+use std::convert::Infallible;
 use std::sync::Arc;
+use tokio::sync::{broadcast::error::RecvError, mpsc};
+use tokio_stream::{wrappers::ReceiverStream, Stream, StreamExt};
 
 use super::types::{
     ApiError, ApiResponse, JobSubmitRequest, JobSubmitResponse,
-    JobStatusResponse, JobListResponse, CapabilitiesResponse,
+    JobStatusResponse, JobListResponse, CapabilitiesResponse, JobCancelResponse,
+    JobControlResponse,
 };
+use crate::cancel::ControlOutcome;
 use crate::capabilities::Capabilities;
+use crate::events::EventBus;
 
 /// Shared API state
 #[derive(Clone)]
@@ -25,6 +33,10 @@ pub struct ApiState {
     pub job_submitter: Arc<dyn JobSubmitter>,
     /// Job status lookup callback
     pub job_status_lookup: Arc<dyn JobStatusLookup>,
+    /// Job cancellation, pause and resume callback
+    pub job_canceller: Arc<dyn JobCanceller>,
+    /// Job event bus for streaming progress
+    pub event_bus: EventBus,
 }
 
 /// Trait for submitting jobs
@@ -41,6 +53,45 @@ pub trait JobStatusLookup: Send + Sync {
     async fn get_result(&self, job_id: &str) -> Option<serde_json::Value>;
 }
 
+/// Trait for cancelling, pausing and resuming jobs
+#[async_trait::async_trait]
+pub trait JobCanceller: Send + Sync {
+    /// Request cancellation
+    async fn cancel_job(&self, job_id: &str) -> Result<ControlOutcome, String>;
+
+    /// Pause a running job at its next checkpoint
+    async fn pause_job(&self, job_id: &str) -> Result<ControlOutcome, String>;
+
+    /// Resume a paused job
+    async fn resume_job(&self, job_id: &str) -> Result<ControlOutcome, String>;
+}
+
+/// Fail with BAD_REQUEST if a job already reached a terminal status
+async fn require_unfinished(state: &ApiState, job_id: &str) -> Result<(), ApiError> {
+    let status = state
+        .job_status_lookup
+        .get_status(job_id)
+        .await
+        .ok_or_else(|| ApiError::not_found(format!("Job {} not found", job_id)))?;
+
+    if matches!(
+        status.status,
+        JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled | JobStatus::Timeout
+    ) {
+        return Err(ApiError::bad_request(format!(
+            "Job {} already finished with status {:?}",
+            job_id, status.status
+        )));
+    }
+
+    Ok(())
+}
+
+/// Error for a job the worker has not picked up yet
+fn not_dispatched(job_id: &str) -> ApiError {
+    ApiError::conflict(format!("Job {} has not been picked up by the worker yet", job_id))
+}
+
 /// POST /api/v1/jobs - Submit a new job
 pub async fn submit_job(
     State(state): State<ApiState>,
@@ -53,6 +104,13 @@ pub async fn submit_job(
         return Err(ApiError::validation_error(format!("Job validation failed: {}", e)));
     }
 
+    // Refuse jobs this worker cannot run before queueing them
+    if let Some(ref constraints) = job.constraints {
+        if let Err(unmet) = state.capabilities.check_constraints(constraints) {
+            return Err(ApiError::constraint_not_met(&unmet));
+        }
+    }
+
     // Ensure job has created_at
     if job.created_at.timestamp() == 0 {
         job.created_at = Utc::now();
@@ -85,6 +143,75 @@ pub async fn get_job_status(
     }
 }
 
+/// DELETE /api/v1/jobs/:id - Cancel a queued or running job
+pub async fn cancel_job(
+    State(state): State<ApiState>,
+    Path(job_id): Path<String>,
+) -> Result<Json<ApiResponse<JobCancelResponse>>, ApiError> {
+    require_unfinished(&state, &job_id).await?;
+
+    match state.job_canceller.cancel_job(&job_id).await {
+        Ok(ControlOutcome::Applied) => Ok(Json(ApiResponse::success(JobCancelResponse {
+            job_id: job_id.clone(),
+            status: "cancelling".to_string(),
+            message: format!("Cancellation requested for job {}", job_id),
+        }))),
+        Ok(ControlOutcome::Unchanged) => Ok(Json(ApiResponse::success(JobCancelResponse {
+            job_id: job_id.clone(),
+            status: "cancelling".to_string(),
+            message: format!("Cancellation already requested for job {}", job_id),
+        }))),
+        Ok(ControlOutcome::NotFound) => Err(not_dispatched(&job_id)),
+        Err(e) => Err(ApiError::internal_error(format!("Failed to cancel job: {}", e))),
+    }
+}
+
+/// POST /api/v1/jobs/:id/pause - Pause a running job at its next checkpoint
+pub async fn pause_job(
+    State(state): State<ApiState>,
+    Path(job_id): Path<String>,
+) -> Result<Json<ApiResponse<JobControlResponse>>, ApiError> {
+    require_unfinished(&state, &job_id).await?;
+
+    match state.job_canceller.pause_job(&job_id).await {
+        Ok(ControlOutcome::Applied) => Ok(Json(ApiResponse::success(JobControlResponse {
+            job_id: job_id.clone(),
+            status: "pausing".to_string(),
+            message: format!("Pause requested for job {}", job_id),
+        }))),
+        Ok(ControlOutcome::Unchanged) => Ok(Json(ApiResponse::success(JobControlResponse {
+            job_id: job_id.clone(),
+            status: "pausing".to_string(),
+            message: format!("Job {} is already paused or cancelled", job_id),
+        }))),
+        Ok(ControlOutcome::NotFound) => Err(not_dispatched(&job_id)),
+        Err(e) => Err(ApiError::internal_error(format!("Failed to pause job: {}", e))),
+    }
+}
+
+/// POST /api/v1/jobs/:id/resume - Resume a paused job
+pub async fn resume_job(
+    State(state): State<ApiState>,
+    Path(job_id): Path<String>,
+) -> Result<Json<ApiResponse<JobControlResponse>>, ApiError> {
+    require_unfinished(&state, &job_id).await?;
+
+    match state.job_canceller.resume_job(&job_id).await {
+        Ok(ControlOutcome::Applied) => Ok(Json(ApiResponse::success(JobControlResponse {
+            job_id: job_id.clone(),
+            status: "resuming".to_string(),
+            message: format!("Resume requested for job {}", job_id),
+        }))),
+        Ok(ControlOutcome::Unchanged) => Ok(Json(ApiResponse::success(JobControlResponse {
+            job_id: job_id.clone(),
+            status: "running".to_string(),
+            message: format!("Job {} is not paused", job_id),
+        }))),
+        Ok(ControlOutcome::NotFound) => Err(not_dispatched(&job_id)),
+        Err(e) => Err(ApiError::internal_error(format!("Failed to resume job: {}", e))),
+    }
+}
+
 /// GET /api/v1/jobs/:id/result - Get job result
 pub async fn get_job_result(
     State(state): State<ApiState>,
@@ -96,6 +223,64 @@ pub async fn get_job_result(
     }
 }
 
+/// GET /api/v1/jobs/:id/events - Stream job progress and state changes (SSE)
+///
+/// Sends a `status` snapshot first, then `progress` and `state` events until
+/// the job reaches a terminal state.
+pub async fn stream_job_events(
+    State(state): State<ApiState>,
+    Path(job_id): Path<String>,
+) -> Result<Sse<impl Stream<Item = Result<Event, Infallible>>>, ApiError> {
+    // Subscribe before the lookup so no transition slips in between
+    let mut events = state.event_bus.subscribe();
+
+    let status = state
+        .job_status_lookup
+        .get_status(&job_id)
+        .await
+        .ok_or_else(|| ApiError::not_found(format!("Job {} not found", job_id)))?;
+
+    let finished = matches!(
+        status.status,
+        JobStatus::Completed | JobStatus::Failed | JobStatus::Cancelled | JobStatus::Timeout
+    );
+
+    let (tx, rx) = mpsc::channel(64);
+
+    tokio::spawn(async move {
+        if tx.send(sse_event("status", &status)).await.is_err() || finished {
+            return;
+        }
+
+        loop {
+            match events.recv().await {
+                Ok(event) if event.job_id() == job_id => {
+                    let terminal = event.is_terminal();
+                    if tx.send(sse_event(event.name(), &event)).await.is_err() || terminal {
+                        break;
+                    }
+                }
+                Ok(_) => {}
+                Err(RecvError::Lagged(skipped)) => {
+                    log::warn!("Event stream for job {} lagged, skipped {} events", job_id, skipped);
+                }
+                Err(RecvError::Closed) => break,
+            }
+        }
+    });
+
+    let stream = ReceiverStream::new(rx).map(Ok::<_, Infallible>);
+    Ok(Sse::new(stream).keep_alive(KeepAlive::default()))
+}
+
+/// Build a named SSE event with a JSON payload
+fn sse_event<T: Serialize>(name: &str, data: &T) -> Event {
+    Event::default()
+        .event(name)
+        .json_data(data)
+        .unwrap_or_else(|e| Event::default().event("error").data(e.to_string()))
+}
+
 /// GET /api/v1/jobs - List all jobs
 pub async fn list_jobs(
     State(state): State<ApiState>,
@@ -115,8 +300,14 @@ pub async fn get_capabilities(
         operations: state.capabilities.operations.clone(),
         features: state.capabilities.features.clone(),
         disk_formats: state.capabilities.disk_formats.clone(),
+        protocol_versions: ProtocolVersion::SUPPORTED
+            .iter()
+            .map(ToString::to_string)
+            .collect(),
         max_concurrent_jobs: state.capabilities.max_concurrent_jobs,
         max_disk_size_gb: state.capabilities.max_disk_size_gb,
+        cpu_cores: state.capabilities.cpu_cores,
+        memory_gb: state.capabilities.memory_gb,
     };
 
     Json(ApiResponse::success(response))
@@ -133,6 +324,7 @@ pub async fn health_check() -> Json<ApiResponse<serde_json::Value>> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::cancel::CancelRegistry;
     use guestkit_job_spec::builder::JobBuilder;
 
     struct MockJobSubmitter;
@@ -172,6 +364,8 @@ mod tests {
             capabilities: Capabilities::new(),
             job_submitter: Arc::new(MockJobSubmitter),
             job_status_lookup: Arc::new(MockJobStatusLookup),
+            job_canceller: Arc::new(CancelRegistry::new()),
+            event_bus: EventBus::new(),
         }
     }
 
@@ -196,6 +390,30 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_submit_job_unmet_constraint() {
+        let mut state = create_test_state();
+        state.capabilities = Capabilities::new().with_resources(4, 8);
+
+        let job = JobBuilder::new()
+            .job_id("test-job-002")
+            .operation("test.operation")
+            .payload("test.operation.v1", serde_json::json!({}))
+            .min_resources(2, 64)
+            .build()
+            .unwrap();
+
+        let error = submit_job(State(state), Json(JobSubmitRequest { job }))
+            .await
+            .unwrap_err();
+
+        assert_eq!(error.error, "CONSTRAINT_NOT_MET");
+        let details = error.details.unwrap();
+        assert_eq!(details["constraint"], "constraints.min_memory_gb");
+        assert_eq!(details["required"], 64);
+        assert_eq!(details["available"], 8);
+    }
+
     #[tokio::test]
     async fn test_get_job_status() {
         let state = create_test_state();
@@ -208,6 +426,63 @@ mod tests {
         assert!(result.is_ok());
     }
 
+    #[tokio::test]
+    async fn test_cancel_job() {
+        let mut state = create_test_state();
+        let cancellations = CancelRegistry::new();
+        cancellations.register("test-job-001");
+        state.job_canceller = Arc::new(cancellations.clone());
+
+        let result = cancel_job(
+            State(state.clone()),
+            Path("test-job-001".to_string()),
+        ).await;
+
+        assert_eq!(result.unwrap().0.data.status, "cancelling");
+        assert!(cancellations.is_cancelled("test-job-001"));
+
+        // Not yet picked up by the worker
+        let error = cancel_job(State(state), Path("test-job-002".to_string()))
+            .await
+            .unwrap_err();
+        assert_eq!(error.error, "CONFLICT");
+    }
+
+    #[tokio::test]
+    async fn test_pause_resume_job() {
+        let mut state = create_test_state();
+        let cancellations = CancelRegistry::new();
+        let token = cancellations.register("test-job-001");
+        state.job_canceller = Arc::new(cancellations);
+
+        let paused = pause_job(State(state.clone()), Path("test-job-001".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(paused.0.data.status, "pausing");
+        assert!(token.is_paused());
+
+        let resumed = resume_job(State(state), Path("test-job-001".to_string()))
+            .await
+            .unwrap();
+        assert_eq!(resumed.0.data.status, "resuming");
+        assert!(!token.is_paused());
+    }
+
+    #[tokio::test]
+    async fn test_stream_job_events() {
+        let state = create_test_state();
+        let event_bus = state.event_bus.clone();
+
+        let result = stream_job_events(
+            State(state),
+            Path("test-job-001".to_string()),
+        ).await;
+        assert!(result.is_ok());
+
+        // The forwarding task is subscribed once the handler returns
+        assert_eq!(event_bus.subscriber_count(), 1);
+    }
+
     #[tokio::test]
     async fn test_health_check() {
         let result = health_check().await;
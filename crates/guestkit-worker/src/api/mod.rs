@@ -5,4 +5,4 @@ pub mod server;
 pub mod types;
 
 pub use server::{ApiServer, ApiServerConfig};
-pub use types::{ApiError, ApiResponse, JobSubmitRequest, JobStatusResponse};
+pub use types::{ApiError, ApiResponse, JobSubmitRequest, JobStatusResponse, JobCancelResponse, JobControlResponse};
@@ -1,7 +1,7 @@
 //! REST API server
 
 use axum::{
-    routing::{get, post},
+    routing::{delete, get, post},
     Router,
 };
 use std::net::SocketAddr;
@@ -10,7 +10,8 @@ use tower_http::trace::TraceLayer;
 
 use super::handlers::{
     ApiState, submit_job, get_job_status, get_job_result,
-    list_jobs, get_capabilities, health_check,
+    list_jobs, get_capabilities, health_check, stream_job_events, cancel_job,
+    pause_job, resume_job,
 };
 
 /// API server configuration
@@ -49,7 +50,11 @@ impl ApiServer {
             .route("/api/v1/jobs", post(submit_job))
             .route("/api/v1/jobs", get(list_jobs))
             .route("/api/v1/jobs/:id", get(get_job_status))
+            .route("/api/v1/jobs/:id", delete(cancel_job))
+            .route("/api/v1/jobs/:id/pause", post(pause_job))
+            .route("/api/v1/jobs/:id/resume", post(resume_job))
             .route("/api/v1/jobs/:id/result", get(get_job_result))
+            .route("/api/v1/jobs/:id/events", get(stream_job_events))
             // Worker endpoints
             .route("/api/v1/capabilities", get(get_capabilities))
             // Health check
@@ -118,6 +123,8 @@ mod tests {
             capabilities: Capabilities::new(),
             job_submitter: Arc::new(MockJobSubmitter),
             job_status_lookup: Arc::new(MockJobStatusLookup),
+            job_canceller: Arc::new(crate::cancel::CancelRegistry::new()),
+            event_bus: crate::events::EventBus::new(),
         };
 
         let server = ApiServer::new(config, state);
@@ -6,7 +6,8 @@ use axum::{
     Json,
 };
 use guestkit_job_spec::{JobDocument, JobStatus};
-use serde::{Deserialize, Serialize};
+use crate::capabilities::UnmetConstraint;
+use serde::{Deserialize, Deserializer, Serialize};
 
 /// API error response
 #[derive(Debug, Serialize, Deserialize)]
@@ -39,6 +40,10 @@ impl ApiError {
         Self::new("NOT_FOUND", message)
     }
 
+    pub fn conflict(message: impl Into<String>) -> Self {
+        Self::new("CONFLICT", message)
+    }
+
     pub fn internal_error(message: impl Into<String>) -> Self {
         Self::new("INTERNAL_ERROR", message)
     }
@@ -46,6 +51,14 @@ impl ApiError {
     pub fn validation_error(message: impl Into<String>) -> Self {
         Self::new("VALIDATION_ERROR", message)
     }
+
+    pub fn constraint_not_met(unmet: &UnmetConstraint) -> Self {
+        Self::new(
+            "CONSTRAINT_NOT_MET",
+            format!("Worker does not meet job constraints: {}", unmet),
+        )
+        .with_details(serde_json::to_value(unmet).unwrap_or_default())
+    }
 }
 
 impl IntoResponse for ApiError {
@@ -53,6 +66,8 @@ impl IntoResponse for ApiError {
         let status = match self.error.as_str() {
             "BAD_REQUEST" | "VALIDATION_ERROR" => StatusCode::BAD_REQUEST,
             "NOT_FOUND" => StatusCode::NOT_FOUND,
+            "CONFLICT" => StatusCode::CONFLICT,
+            "CONSTRAINT_NOT_MET" => StatusCode::UNPROCESSABLE_ENTITY,
             _ => StatusCode::INTERNAL_SERVER_ERROR,
         };
 
@@ -83,13 +98,22 @@ impl<T: Serialize> IntoResponse for ApiResponse<T> {
 }
 
 /// Job submission request
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize)]
 pub struct JobSubmitRequest {
     /// Job document (can be partial, server will fill in defaults)
     #[serde(flatten)]
     pub job: JobDocument,
 }
 
+impl<'de> Deserialize<'de> for JobSubmitRequest {
+    /// Accepts any supported protocol version, upconverting older documents
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let value = serde_json::Value::deserialize(deserializer)?;
+        let job = JobDocument::from_value_any(value).map_err(serde::de::Error::custom)?;
+        Ok(Self { job })
+    }
+}
+
 /// Job submission response
 #[derive(Debug, Serialize, Deserialize)]
 pub struct JobSubmitResponse {
@@ -98,6 +122,22 @@ pub struct JobSubmitResponse {
     pub message: String,
 }
 
+/// Job cancellation response
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JobCancelResponse {
+    pub job_id: String,
+    pub status: String,
+    pub message: String,
+}
+
+/// Job pause/resume response
+#[derive(Debug, Serialize, Deserialize)]
+pub struct JobControlResponse {
+    pub job_id: String,
+    pub status: String,
+    pub message: String,
+}
+
 /// Job status response
 #[derive(Debug, Serialize, Deserialize)]
 pub struct JobStatusResponse {
@@ -124,8 +164,11 @@ pub struct CapabilitiesResponse {
     pub operations: Vec<String>,
     pub features: Vec<String>,
     pub disk_formats: Vec<String>,
+    pub protocol_versions: Vec<String>,
     pub max_concurrent_jobs: usize,
     pub max_disk_size_gb: u64,
+    pub cpu_cores: u32,
+    pub memory_gb: u64,
 }
 
 #[cfg(test)]
@@ -0,0 +1,648 @@
+//! Remote artifact fetch and upload
+//!
+//! Jobs may reference source images by `s3://bucket/key` or `http(s)://`
+//! URL instead of a local path. The artifact store downloads them into a
+//! per-job scratch directory (verifying the checksum while streaming) and,
+//! when an upload URL is configured, copies each job's output file and
+//! artifacts back to object storage after the job succeeds.
+//!
+//! S3 requests are signed with AWS Signature V4 using the standard
+//! `AWS_ACCESS_KEY_ID` / `AWS_SECRET_ACCESS_KEY` / `AWS_SESSION_TOKEN`
+//! environment variables; without credentials they are sent unsigned, which
+//! works for public buckets. Any S3-compatible endpoint (MinIO, Ceph RGW)
+//! can be used through `s3_endpoint`.
+
+use std::path::{Path, PathBuf};
+use ring::hmac;
+use sha2::{Digest, Sha256};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use crate::error::{WorkerError, WorkerResult};
+use crate::handler::HandlerResult;
+
+/// Largest object sent with a single PUT; bigger uploads to S3 use multipart
+const SINGLE_PUT_LIMIT: u64 = 5 * 1024 * 1024 * 1024;
+
+/// Multipart upload part size
+const PART_SIZE: usize = 64 * 1024 * 1024;
+
+/// Artifact fetch/upload configuration
+#[derive(Debug, Clone)]
+pub struct ArtifactConfig {
+    /// Where downloaded images are staged (defaults to `<work_dir>/scratch`)
+    pub scratch_dir: Option<PathBuf>,
+
+    /// Upload outputs under this prefix (`s3://bucket/prefix` or `https://host/path`)
+    pub upload_url: Option<String>,
+
+    /// S3-compatible endpoint (defaults to AWS for `s3_region`)
+    pub s3_endpoint: Option<String>,
+
+    /// Region used for request signing
+    pub s3_region: String,
+
+    /// Keep downloaded images after the job finishes
+    pub keep_downloads: bool,
+}
+
+impl Default for ArtifactConfig {
+    fn default() -> Self {
+        Self {
+            scratch_dir: None,
+            upload_url: None,
+            s3_endpoint: None,
+            s3_region: "us-east-1".to_string(),
+            keep_downloads: false,
+        }
+    }
+}
+
+/// Static credentials for S3 request signing
+#[derive(Clone)]
+struct S3Credentials {
+    access_key: String,
+    secret_key: String,
+    session_token: Option<String>,
+}
+
+impl std::fmt::Debug for S3Credentials {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("S3Credentials")
+            .field("access_key", &self.access_key)
+            .finish_non_exhaustive()
+    }
+}
+
+impl S3Credentials {
+    fn from_env() -> Option<Self> {
+        let access_key = std::env::var("AWS_ACCESS_KEY_ID").ok()?;
+        let secret_key = std::env::var("AWS_SECRET_ACCESS_KEY").ok()?;
+        Some(Self {
+            access_key,
+            secret_key,
+            session_token: std::env::var("AWS_SESSION_TOKEN").ok(),
+        })
+    }
+}
+
+/// Parsed remote reference
+#[derive(Debug, Clone, PartialEq)]
+enum RemoteLocation {
+    S3 { bucket: String, key: String },
+    Http(String),
+}
+
+impl RemoteLocation {
+    fn parse(reference: &str) -> WorkerResult<Self> {
+        if let Some(rest) = reference.strip_prefix("s3://") {
+            let (bucket, key) = rest.split_once('/').unwrap_or((rest, ""));
+            if bucket.is_empty() {
+                return Err(WorkerError::InvalidConfig(format!(
+                    "S3 reference has no bucket: {}",
+                    reference
+                )));
+            }
+            return Ok(Self::S3 {
+                bucket: bucket.to_string(),
+                key: key.trim_start_matches('/').to_string(),
+            });
+        }
+        if reference.starts_with("https://") || reference.starts_with("http://") {
+            return Ok(Self::Http(reference.to_string()));
+        }
+        Err(WorkerError::InvalidConfig(format!(
+            "Unsupported artifact URL: {}",
+            reference
+        )))
+    }
+
+    /// Location of a child object
+    fn join(&self, name: &str) -> Self {
+        match self {
+            Self::S3 { bucket, key } => Self::S3 {
+                bucket: bucket.clone(),
+                key: join_key(key, name),
+            },
+            Self::Http(url) => Self::Http(join_key(url, name)),
+        }
+    }
+
+    /// Last path component, used as the local file name
+    fn file_name(&self) -> String {
+        let path = match self {
+            Self::S3 { key, .. } => key.as_str(),
+            Self::Http(url) => url.split(['?', '#']).next().unwrap_or(url),
+        };
+        let name = path.rsplit('/').find(|segment| !segment.is_empty()).unwrap_or_default();
+        // "." and ".." have no file name and would leave the scratch directory
+        Path::new(name)
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or("artifact")
+            .to_string()
+    }
+
+    fn to_url(&self) -> String {
+        match self {
+            Self::S3 { bucket, key } => format!("s3://{}/{}", bucket, key),
+            Self::Http(url) => url.clone(),
+        }
+    }
+}
+
+fn join_key(prefix: &str, name: &str) -> String {
+    if prefix.is_empty() || prefix.ends_with('/') {
+        format!("{}{}", prefix, name)
+    } else {
+        format!("{}/{}", prefix, name)
+    }
+}
+
+/// Whether an image reference points at object storage or a web server
+pub fn is_remote(reference: &str) -> bool {
+    reference.starts_with("s3://")
+        || reference.starts_with("https://")
+        || reference.starts_with("http://")
+}
+
+/// Expected SHA-256 digest from a `sha256:<hex>` or bare hex checksum
+fn expected_sha256(checksum: &str) -> WorkerResult<String> {
+    let (algorithm, hash) = checksum.split_once(':').unwrap_or(("sha256", checksum));
+    if !algorithm.eq_ignore_ascii_case("sha256") {
+        return Err(WorkerError::ExecutionError(format!(
+            "Unsupported checksum algorithm: {}. Only 'sha256' is supported.",
+            algorithm
+        )));
+    }
+    Ok(hash.to_lowercase())
+}
+
+fn hmac_sha256(key: &[u8], data: &[u8]) -> Vec<u8> {
+    let key = hmac::Key::new(hmac::HMAC_SHA256, key);
+    hmac::sign(&key, data).as_ref().to_vec()
+}
+
+/// Percent-encode an S3 key, keeping `/` separators
+fn uri_encode(value: &str, keep_slash: bool) -> String {
+    let mut out = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'_' | b'.' | b'~' => {
+                out.push(byte as char)
+            }
+            b'/' if keep_slash => out.push('/'),
+            _ => out.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    out
+}
+
+/// A signed (or anonymous) S3 request ready to send
+struct S3Request {
+    url: String,
+    headers: Vec<(String, String)>,
+}
+
+/// Downloads remote images and uploads job outputs
+#[derive(Debug)]
+pub struct ArtifactStore {
+    config: ArtifactConfig,
+    scratch_dir: PathBuf,
+    client: reqwest::Client,
+    credentials: Option<S3Credentials>,
+}
+
+impl ArtifactStore {
+    /// Create a store; the scratch directory defaults to `<work_dir>/scratch`
+    pub fn new(config: ArtifactConfig, work_dir: &Path) -> WorkerResult<Self> {
+        if let Some(ref upload_url) = config.upload_url {
+            RemoteLocation::parse(upload_url)?;
+        }
+
+        let scratch_dir = config
+            .scratch_dir
+            .clone()
+            .unwrap_or_else(|| work_dir.join("scratch"));
+
+        Ok(Self {
+            config,
+            scratch_dir,
+            client: reqwest::Client::new(),
+            credentials: S3Credentials::from_env(),
+        })
+    }
+
+    /// Whether job outputs are uploaded after success
+    pub fn uploads_enabled(&self) -> bool {
+        self.config.upload_url.is_some()
+    }
+
+    fn job_scratch_dir(&self, job_id: &str) -> PathBuf {
+        self.scratch_dir.join(job_id)
+    }
+
+    fn s3_endpoint(&self) -> String {
+        self.config
+            .s3_endpoint
+            .clone()
+            .unwrap_or_else(|| format!("https://s3.{}.amazonaws.com", self.config.s3_region))
+            .trim_end_matches('/')
+            .to_string()
+    }
+
+    /// Build a path-style S3 request, signed when credentials are available
+    fn s3_request(&self, method: &str, bucket: &str, key: &str, query: &str) -> WorkerResult<S3Request> {
+        let endpoint = self.s3_endpoint();
+        let host = endpoint
+            .split("://")
+            .nth(1)
+            .unwrap_or(&endpoint)
+            .split('/')
+            .next()
+            .unwrap_or_default()
+            .to_string();
+        let canonical_uri = format!("/{}/{}", uri_encode(bucket, false), uri_encode(key, true));
+        let url = if query.is_empty() {
+            format!("{}{}", endpoint, canonical_uri)
+        } else {
+            format!("{}{}?{}", endpoint, canonical_uri, query)
+        };
+
+        let Some(ref credentials) = self.credentials else {
+            return Ok(S3Request { url, headers: Vec::new() });
+        };
+
+        let now = chrono::Utc::now();
+        let amz_date = now.format("%Y%m%dT%H%M%SZ").to_string();
+        let date = now.format("%Y%m%d").to_string();
+        let payload_hash = "UNSIGNED-PAYLOAD";
+
+        let mut headers = vec![
+            ("host".to_string(), host),
+            ("x-amz-content-sha256".to_string(), payload_hash.to_string()),
+            ("x-amz-date".to_string(), amz_date.clone()),
+        ];
+        if let Some(ref token) = credentials.session_token {
+            headers.push(("x-amz-security-token".to_string(), token.clone()));
+        }
+
+        let canonical_headers: String = headers
+            .iter()
+            .map(|(name, value)| format!("{}:{}\n", name, value.trim()))
+            .collect();
+        let signed_headers = headers
+            .iter()
+            .map(|(name, _)| name.as_str())
+            .collect::<Vec<_>>()
+            .join(";");
+
+        let canonical_request = format!(
+            "{}\n{}\n{}\n{}\n{}\n{}",
+            method, canonical_uri, query, canonical_headers, signed_headers, payload_hash
+        );
+        let scope = format!("{}/{}/s3/aws4_request", date, self.config.s3_region);
+        let string_to_sign = format!(
+            "AWS4-HMAC-SHA256\n{}\n{}\n{}",
+            amz_date,
+            scope,
+            hex::encode(Sha256::digest(canonical_request.as_bytes()))
+        );
+
+        let mut key = hmac_sha256(
+            format!("AWS4{}", credentials.secret_key).as_bytes(),
+            date.as_bytes(),
+        );
+        for part in [self.config.s3_region.as_str(), "s3", "aws4_request"] {
+            key = hmac_sha256(&key, part.as_bytes());
+        }
+        let signature = hex::encode(hmac_sha256(&key, string_to_sign.as_bytes()));
+
+        headers.retain(|(name, _)| name != "host");
+        headers.push((
+            "authorization".to_string(),
+            format!(
+                "AWS4-HMAC-SHA256 Credential={}/{}, SignedHeaders={}, Signature={}",
+                credentials.access_key, scope, signed_headers, signature
+            ),
+        ));
+
+        Ok(S3Request { url, headers })
+    }
+
+    fn request(&self, method: reqwest::Method, location: &RemoteLocation, query: &str) -> WorkerResult<reqwest::RequestBuilder> {
+        let builder = match location {
+            RemoteLocation::S3 { bucket, key } => {
+                let signed = self.s3_request(method.as_str(), bucket, key, query)?;
+                let mut builder = self.client.request(method, &signed.url);
+                for (name, value) in signed.headers {
+                    builder = builder.header(name, value);
+                }
+                builder
+            }
+            RemoteLocation::Http(url) => self.client.request(method, url),
+        };
+        Ok(builder)
+    }
+
+    async fn send(builder: reqwest::RequestBuilder, what: &str) -> WorkerResult<reqwest::Response> {
+        let response = builder
+            .send()
+            .await
+            .map_err(|e| WorkerError::TransportError(format!("{}: {}", what, e)))?;
+        if !response.status().is_success() {
+            let status = response.status();
+            let body = response.text().await.unwrap_or_default();
+            return Err(WorkerError::TransportError(format!(
+                "{}: HTTP {} {}",
+                what,
+                status,
+                body.chars().take(200).collect::<String>()
+            )));
+        }
+        Ok(response)
+    }
+
+    /// Download a remote image into the job's scratch directory
+    ///
+    /// The checksum, if given, is verified while streaming; a mismatching
+    /// download is deleted. Returns the local path and its size.
+    pub async fn fetch(
+        &self,
+        job_id: &str,
+        reference: &str,
+        checksum: Option<&str>,
+    ) -> WorkerResult<(PathBuf, u64)> {
+        let location = RemoteLocation::parse(reference)?;
+        let expected = checksum.map(expected_sha256).transpose()?;
+
+        let dir = self.job_scratch_dir(job_id);
+        tokio::fs::create_dir_all(&dir).await?;
+        let local_path = dir.join(location.file_name());
+
+        log::info!("Fetching {} to {}", reference, local_path.display());
+
+        let mut response = Self::send(
+            self.request(reqwest::Method::GET, &location, "")?,
+            &format!("Failed to fetch {}", reference),
+        )
+        .await?;
+
+        let mut file = tokio::fs::File::create(&local_path).await?;
+        let mut hasher = Sha256::new();
+        let mut bytes = 0u64;
+
+        while let Some(chunk) = response
+            .chunk()
+            .await
+            .map_err(|e| WorkerError::TransportError(format!("Failed to fetch {}: {}", reference, e)))?
+        {
+            hasher.update(&chunk);
+            file.write_all(&chunk).await?;
+            bytes += chunk.len() as u64;
+        }
+        file.flush().await?;
+
+        if let Some(expected) = expected {
+            let computed = format!("{:x}", hasher.finalize());
+            if computed != expected {
+                let _ = tokio::fs::remove_file(&local_path).await;
+                return Err(WorkerError::ExecutionError(format!(
+                    "Checksum verification failed for {}: expected {}, got {}",
+                    reference, expected, computed
+                )));
+            }
+        }
+
+        log::info!("Fetched {} ({} bytes)", reference, bytes);
+        Ok((local_path, bytes))
+    }
+
+    /// Upload a file, or every file below a directory, under the job's prefix
+    ///
+    /// Returns the remote URL of the uploaded file or directory.
+    pub async fn upload(&self, job_id: &str, local: &Path) -> WorkerResult<String> {
+        let upload_url = self.config.upload_url.as_deref().ok_or_else(|| {
+            WorkerError::InvalidConfig("No upload URL configured".to_string())
+        })?;
+        let name = local
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| "artifact".to_string());
+        let base = RemoteLocation::parse(upload_url)?.join(job_id).join(&name);
+
+        if tokio::fs::metadata(local).await?.is_dir() {
+            let mut pending = vec![(local.to_path_buf(), base.clone())];
+            while let Some((dir, remote)) = pending.pop() {
+                let mut entries = tokio::fs::read_dir(&dir).await?;
+                while let Some(entry) = entries.next_entry().await? {
+                    let child = remote.join(&entry.file_name().to_string_lossy());
+                    if entry.file_type().await?.is_dir() {
+                        pending.push((entry.path(), child));
+                    } else {
+                        self.upload_file(&entry.path(), &child).await?;
+                    }
+                }
+            }
+            return Ok(format!("{}/", base.to_url()));
+        }
+
+        self.upload_file(local, &base).await?;
+        Ok(base.to_url())
+    }
+
+    async fn upload_file(&self, local: &Path, location: &RemoteLocation) -> WorkerResult<()> {
+        let size = tokio::fs::metadata(local).await?.len();
+        log::info!("Uploading {} to {} ({} bytes)", local.display(), location.to_url(), size);
+
+        if size > SINGLE_PUT_LIMIT {
+            if let RemoteLocation::S3 { .. } = location {
+                return self.upload_multipart(local, location).await;
+            }
+        }
+
+        let file = tokio::fs::File::open(local).await?;
+        let builder = self
+            .request(reqwest::Method::PUT, location, "")?
+            .header(reqwest::header::CONTENT_LENGTH, size)
+            .body(reqwest::Body::from(file));
+        Self::send(builder, &format!("Failed to upload {}", local.display())).await?;
+        Ok(())
+    }
+
+    /// S3 multipart upload for objects above the single PUT limit
+    async fn upload_multipart(&self, local: &Path, location: &RemoteLocation) -> WorkerResult<()> {
+        let what = format!("Failed to upload {}", local.display());
+
+        let response = Self::send(self.request(reqwest::Method::POST, location, "uploads=")?, &what).await?;
+        let body = response
+            .text()
+            .await
+            .map_err(|e| WorkerError::TransportError(format!("{}: {}", what, e)))?;
+        let upload_id = xml_element(&body, "UploadId").ok_or_else(|| {
+            WorkerError::TransportError(format!("{}: no UploadId in response", what))
+        })?;
+
+        let mut file = tokio::fs::File::open(local).await?;
+        let mut parts = Vec::new();
+        let mut buffer = vec![0u8; PART_SIZE];
+        loop {
+            let mut filled = 0;
+            while filled < PART_SIZE {
+                let n = file.read(&mut buffer[filled..]).await?;
+                if n == 0 {
+                    break;
+                }
+                filled += n;
+            }
+            if filled == 0 {
+                break;
+            }
+
+            let part_number = parts.len() + 1;
+            let query = format!(
+                "partNumber={}&uploadId={}",
+                part_number,
+                uri_encode(&upload_id, false)
+            );
+            let response = Self::send(
+                self.request(reqwest::Method::PUT, location, &query)?
+                    .body(buffer[..filled].to_vec()),
+                &what,
+            )
+            .await?;
+            let etag = response
+                .headers()
+                .get(reqwest::header::ETAG)
+                .and_then(|v| v.to_str().ok())
+                .unwrap_or_default()
+                .to_string();
+            parts.push(etag);
+        }
+
+        let manifest: String = parts
+            .iter()
+            .enumerate()
+            .map(|(i, etag)| format!("<Part><PartNumber>{}</PartNumber><ETag>{}</ETag></Part>", i + 1, etag))
+            .collect();
+        let query = format!("uploadId={}", uri_encode(&upload_id, false));
+        Self::send(
+            self.request(reqwest::Method::POST, location, &query)?
+                .body(format!("<CompleteMultipartUpload>{}</CompleteMultipartUpload>", manifest)),
+            &what,
+        )
+        .await?;
+        Ok(())
+    }
+
+    /// Upload a successful job's output file and artifacts
+    ///
+    /// The returned result references the uploaded copies.
+    pub async fn upload_result(&self, job_id: &str, mut result: HandlerResult) -> WorkerResult<HandlerResult> {
+        if let Some(ref output) = result.output_file {
+            if !output.is_empty() && Path::new(output).exists() {
+                result.output_file = Some(self.upload(job_id, Path::new(output)).await?);
+            }
+        }
+
+        let mut uploaded = Vec::with_capacity(result.artifacts.len());
+        for artifact in &result.artifacts {
+            if Path::new(artifact).exists() {
+                uploaded.push(self.upload(job_id, Path::new(artifact)).await?);
+            } else {
+                uploaded.push(artifact.clone());
+            }
+        }
+        result.artifacts = uploaded;
+
+        Ok(result)
+    }
+
+    /// Remove images downloaded for a job
+    pub async fn cleanup(&self, job_id: &str) {
+        if self.config.keep_downloads {
+            return;
+        }
+        let dir = self.job_scratch_dir(job_id);
+        if dir.exists() {
+            if let Err(e) = tokio::fs::remove_dir_all(&dir).await {
+                log::warn!("Failed to remove scratch directory {}: {}", dir.display(), e);
+            }
+        }
+    }
+}
+
+/// Text of the first `<name>` element in an XML document
+fn xml_element(xml: &str, name: &str) -> Option<String> {
+    let open = format!("<{}>", name);
+    let close = format!("</{}>", name);
+    let start = xml.find(&open)? + open.len();
+    let end = start + xml[start..].find(&close)?;
+    Some(xml[start..end].to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_remote_location() {
+        assert_eq!(
+            RemoteLocation::parse("s3://images/base/web.qcow2").unwrap(),
+            RemoteLocation::S3 {
+                bucket: "images".to_string(),
+                key: "base/web.qcow2".to_string()
+            }
+        );
+        assert!(RemoteLocation::parse("/vms/web.qcow2").is_err());
+        assert!(RemoteLocation::parse("s3:///key").is_err());
+
+        let http = RemoteLocation::parse("https://mirror.example/vm.qcow2?sig=abc").unwrap();
+        assert_eq!(http.file_name(), "vm.qcow2");
+        assert_eq!(RemoteLocation::parse("s3://images/base/..").unwrap().file_name(), "artifact");
+        assert_eq!(RemoteLocation::parse("https://mirror.example/a/./").unwrap().file_name(), "artifact");
+
+        let prefix = RemoteLocation::parse("s3://results/jobs/").unwrap();
+        assert_eq!(
+            prefix.join("job-1").join("report.json").to_url(),
+            "s3://results/jobs/job-1/report.json"
+        );
+    }
+
+    #[test]
+    fn test_hmac_sha256() {
+        // RFC 4231 test case 2
+        assert_eq!(
+            hex::encode(hmac_sha256(b"Jefe", b"what do ya want for nothing?")),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_uri_encode_and_checksum() {
+        assert_eq!(uri_encode("a b/c+d.qcow2", true), "a%20b/c%2Bd.qcow2");
+        assert_eq!(uri_encode("a/b", false), "a%2Fb");
+        assert_eq!(expected_sha256("sha256:ABC").unwrap(), "abc");
+        assert!(expected_sha256("md5:abc").is_err());
+        assert_eq!(
+            xml_element("<R><UploadId>xyz</UploadId></R>", "UploadId").as_deref(),
+            Some("xyz")
+        );
+    }
+
+    #[tokio::test]
+    async fn test_local_references_are_not_remote() {
+        assert!(is_remote("s3://bucket/key"));
+        assert!(is_remote("https://example.com/vm.qcow2"));
+        assert!(!is_remote("/vms/web.qcow2"));
+
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let store = ArtifactStore::new(ArtifactConfig::default(), temp_dir.path()).unwrap();
+        assert!(!store.uploads_enabled());
+        assert!(store.upload("job-1", temp_dir.path()).await.is_err());
+
+        let bad = ArtifactConfig {
+            upload_url: Some("ftp://example.com".to_string()),
+            ..Default::default()
+        };
+        assert!(ArtifactStore::new(bad, temp_dir.path()).is_err());
+    }
+}
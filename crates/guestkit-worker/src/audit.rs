@@ -0,0 +1,119 @@
+//! Audit log records for jobs
+//!
+//! Every job the worker runs is appended to the guestkit operation audit
+//! log. The actor comes from the job's `audit` section (submitter, origin
+//! and authorization), the image and its digest from the payload.
+
+use guestkit::core::{AuditActor, AuditRecord, AuditResult};
+use guestkit_job_spec::JobDocument;
+
+use crate::error::{WorkerError, WorkerResult};
+
+/// Payload fields holding the image a job works on
+const IMAGE_POINTERS: [&str; 2] = ["/image", "/source"];
+
+/// Build the audit record of a job
+///
+/// The image digest is the checksum the job declares, which handlers verify
+/// before touching the image.
+pub fn job_record(job: &JobDocument, worker_id: &str) -> AuditRecord {
+    let audit = job.audit.clone().unwrap_or_default();
+    let mut actor = AuditActor::new(audit.submitted_by.unwrap_or_else(|| "unknown".to_string()));
+    if let Some(source) = audit.submitted_from {
+        actor = actor.with_source(source);
+    }
+    if let Some(authorization) = audit.authorization {
+        actor = actor.with_authorization(format!(
+            "{}:{}",
+            authorization.method, authorization.subject
+        ));
+    }
+
+    let mut record = AuditRecord::new(&job.operation, actor).with_parameters(serde_json::json!({
+        "job_id": job.job_id,
+        "worker_id": worker_id,
+        "payload": job.payload.data,
+    }));
+
+    let image = IMAGE_POINTERS
+        .iter()
+        .find_map(|pointer| job.payload.data.pointer(pointer));
+    if let Some(path) = image.and_then(|image| image["path"].as_str()) {
+        record = record.with_image(path);
+    }
+    if let Some(checksum) = image.and_then(|image| image["checksum"].as_str()) {
+        record = record.with_image_digest(if checksum.contains(':') {
+            checksum.to_lowercase()
+        } else {
+            format!("sha256:{}", checksum.to_lowercase())
+        });
+    }
+
+    record
+}
+
+/// Audit result of a finished job
+pub fn job_result(outcome: &WorkerResult<()>) -> AuditResult {
+    match outcome {
+        Ok(()) => AuditResult::success(),
+        Err(WorkerError::Cancelled(_)) => AuditResult::cancelled("Job cancelled by request"),
+        Err(e) => AuditResult::failure(e.to_string()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use guestkit::core::AuditStatus;
+    use guestkit_job_spec::types::Authorization;
+    use guestkit_job_spec::{Audit, JobBuilder};
+
+    #[test]
+    fn test_job_record() {
+        let mut job = JobBuilder::new()
+            .job_id("job-00000001")
+            .operation("guestkit.inspect")
+            .payload(
+                "guestkit.inspect.v1",
+                serde_json::json!({
+                    "image": { "path": "/vms/web.qcow2", "format": "qcow2", "checksum": "ABC123" }
+                }),
+            )
+            .build()
+            .unwrap();
+        job.audit = Some(Audit {
+            submitted_by: Some("alice".to_string()),
+            submitted_from: Some("10.0.0.5".to_string()),
+            authorization: Some(Authorization {
+                method: "oidc".to_string(),
+                subject: "alice@example.com".to_string(),
+            }),
+            signature: None,
+        });
+
+        let record = job_record(&job, "worker-1");
+        assert_eq!(record.operation, "guestkit.inspect");
+        assert_eq!(record.image.as_deref(), Some("/vms/web.qcow2"));
+        assert_eq!(record.image_digest.as_deref(), Some("sha256:abc123"));
+        assert_eq!(record.actor.user, "alice");
+        assert_eq!(record.actor.source.as_deref(), Some("10.0.0.5"));
+        assert_eq!(
+            record.actor.authorization.as_deref(),
+            Some("oidc:alice@example.com")
+        );
+        assert_eq!(record.parameters["job_id"], "job-00000001");
+    }
+
+    #[test]
+    fn test_job_result() {
+        assert_eq!(job_result(&Ok(())).status, AuditStatus::Success);
+        assert_eq!(
+            job_result(&Err(WorkerError::Cancelled("job-00000001".to_string()))).status,
+            AuditStatus::Cancelled
+        );
+        assert_eq!(
+            job_result(&Err(WorkerError::Timeout { seconds: 5 })).status,
+            AuditStatus::Failure
+        );
+    }
+}
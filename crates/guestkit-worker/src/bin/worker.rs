@@ -8,6 +8,9 @@
 //! - `submit` - Submit a job to the worker
 //! - `status` - Get job status
 //! - `result` - Get job result
+//! - `cancel` - Cancel a queued or running job
+//! - `pause` - Pause a running job at its next checkpoint
+//! - `resume` - Resume a paused job
 //! - `list` - List all jobs
 //! - `capabilities` - Get worker capabilities
 //! - `health` - Check worker health
@@ -0,0 +1,288 @@
+//! Cooperative job cancellation and pausing
+
+use dashmap::DashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use tokio::sync::Notify;
+use crate::api::handlers::JobCanceller;
+
+/// Cancellation and pause signal shared between the executor and a running handler
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+    paused: Arc<AtomicBool>,
+    notify: Arc<Notify>,
+}
+
+impl CancelToken {
+    /// Create a new, uncancelled token
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Request cancellation
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    /// Check if cancellation was requested
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+    }
+
+    /// Wait until cancellation is requested
+    pub async fn cancelled(&self) {
+        loop {
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if self.is_cancelled() {
+                return;
+            }
+
+            notified.await;
+        }
+    }
+
+    /// Request that the job stop at its next checkpoint until resumed
+    pub fn pause(&self) {
+        self.paused.store(true, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    /// Let a paused job continue
+    pub fn resume(&self) {
+        self.paused.store(false, Ordering::SeqCst);
+        self.notify.notify_waiters();
+    }
+
+    /// Check if the job is paused
+    pub fn is_paused(&self) -> bool {
+        self.paused.load(Ordering::SeqCst)
+    }
+
+    /// Wait until the job is resumed or cancelled
+    pub async fn resumed(&self) {
+        loop {
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            if !self.is_paused() || self.is_cancelled() {
+                return;
+            }
+
+            notified.await;
+        }
+    }
+
+    /// Wait until the paused flag differs from `paused` and return it
+    pub async fn pause_changed(&self, paused: bool) -> bool {
+        loop {
+            let notified = self.notify.notified();
+            tokio::pin!(notified);
+            notified.as_mut().enable();
+
+            let current = self.is_paused();
+            if current != paused {
+                return current;
+            }
+
+            notified.await;
+        }
+    }
+}
+
+/// Result of a cancel, pause or resume request
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ControlOutcome {
+    /// The request changed the job's signal
+    Applied,
+    /// The job was already in the requested state
+    Unchanged,
+    /// The job has not been dispatched to this worker or has finished
+    NotFound,
+}
+
+/// Registry of cancel tokens keyed by job ID
+///
+/// Tokens are registered when a job is dispatched to the worker, so a job
+/// cancelled while waiting on dependencies or a slot is stopped before it
+/// runs, and removed once the job finishes or is rejected.
+#[derive(Debug, Clone, Default)]
+pub struct CancelRegistry {
+    tokens: Arc<DashMap<String, CancelToken>>,
+}
+
+impl CancelRegistry {
+    /// Create a new registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a dispatched job, returning its token
+    ///
+    /// Registering a job twice returns the existing token.
+    pub fn register(&self, job_id: &str) -> CancelToken {
+        self.tokens
+            .entry(job_id.to_string())
+            .or_default()
+            .clone()
+    }
+
+    /// Get the token of a dispatched job
+    pub fn token(&self, job_id: &str) -> Option<CancelToken> {
+        self.tokens.get(job_id).map(|token| token.clone())
+    }
+
+    /// Request cancellation of a job
+    pub fn cancel(&self, job_id: &str) -> ControlOutcome {
+        match self.token(job_id) {
+            Some(token) if token.is_cancelled() => ControlOutcome::Unchanged,
+            Some(token) => {
+                token.cancel();
+                ControlOutcome::Applied
+            }
+            None => ControlOutcome::NotFound,
+        }
+    }
+
+    /// Pause a job at its next checkpoint
+    pub fn pause(&self, job_id: &str) -> ControlOutcome {
+        match self.token(job_id) {
+            Some(token) if token.is_paused() || token.is_cancelled() => ControlOutcome::Unchanged,
+            Some(token) => {
+                token.pause();
+                ControlOutcome::Applied
+            }
+            None => ControlOutcome::NotFound,
+        }
+    }
+
+    /// Resume a paused job
+    pub fn resume(&self, job_id: &str) -> ControlOutcome {
+        match self.token(job_id) {
+            Some(token) if !token.is_paused() => ControlOutcome::Unchanged,
+            Some(token) => {
+                token.resume();
+                ControlOutcome::Applied
+            }
+            None => ControlOutcome::NotFound,
+        }
+    }
+
+    /// Check if cancellation was requested for a job
+    pub fn is_cancelled(&self, job_id: &str) -> bool {
+        self.tokens
+            .get(job_id)
+            .map(|token| token.is_cancelled())
+            .unwrap_or(false)
+    }
+
+    /// Forget a job's token once it has finished
+    pub fn remove(&self, job_id: &str) {
+        self.tokens.remove(job_id);
+    }
+}
+
+#[async_trait::async_trait]
+impl JobCanceller for CancelRegistry {
+    async fn cancel_job(&self, job_id: &str) -> Result<ControlOutcome, String> {
+        Ok(self.cancel(job_id))
+    }
+
+    async fn pause_job(&self, job_id: &str) -> Result<ControlOutcome, String> {
+        Ok(self.pause(job_id))
+    }
+
+    async fn resume_job(&self, job_id: &str) -> Result<ControlOutcome, String> {
+        Ok(self.resume(job_id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    #[tokio::test]
+    async fn test_cancel_token_wakes_waiter() {
+        let token = CancelToken::new();
+        let waiter = token.clone();
+
+        let handle = tokio::spawn(async move {
+            waiter.cancelled().await;
+        });
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!token.is_cancelled());
+        token.cancel();
+
+        tokio::time::timeout(Duration::from_secs(1), handle)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_pause_blocks_until_resumed() {
+        let token = CancelToken::new();
+        token.pause();
+
+        let waiter = token.clone();
+        let handle = tokio::spawn(async move {
+            waiter.resumed().await;
+        });
+
+        tokio::time::sleep(Duration::from_millis(10)).await;
+        assert!(!handle.is_finished());
+        assert!(tokio::time::timeout(Duration::from_millis(10), token.pause_changed(true))
+            .await
+            .is_err());
+
+        token.resume();
+        assert!(!token.pause_changed(true).await);
+        tokio::time::timeout(Duration::from_secs(1), handle)
+            .await
+            .unwrap()
+            .unwrap();
+    }
+
+    #[test]
+    fn test_registry_cancel_before_start() {
+        let registry = CancelRegistry::new();
+        registry.register("job-1");
+
+        assert_eq!(registry.cancel("job-1"), ControlOutcome::Applied);
+        assert_eq!(registry.cancel("job-1"), ControlOutcome::Unchanged);
+        assert!(registry.is_cancelled("job-1"));
+        assert!(registry.register("job-1").is_cancelled());
+
+        registry.remove("job-1");
+        assert!(registry.token("job-1").is_none());
+    }
+
+    #[test]
+    fn test_registry_ignores_unknown_jobs() {
+        let registry = CancelRegistry::new();
+
+        assert_eq!(registry.cancel("job-1"), ControlOutcome::NotFound);
+        assert_eq!(registry.pause("job-1"), ControlOutcome::NotFound);
+        assert!(registry.token("job-1").is_none());
+    }
+
+    #[test]
+    fn test_registry_pause_resume() {
+        let registry = CancelRegistry::new();
+        let token = registry.register("job-1");
+
+        assert_eq!(registry.resume("job-1"), ControlOutcome::Unchanged);
+        assert_eq!(registry.pause("job-1"), ControlOutcome::Applied);
+        assert_eq!(registry.pause("job-1"), ControlOutcome::Unchanged);
+        assert!(token.is_paused());
+        assert_eq!(registry.resume("job-1"), ControlOutcome::Applied);
+        assert!(!token.is_paused());
+    }
+}
@@ -0,0 +1,17 @@
+//! Cancel command handler
+
+use anyhow::Result;
+use super::commands::CancelArgs;
+use guestkit_client::WorkerClient;
+
+pub async fn run_cancel(args: CancelArgs) -> Result<()> {
+    let client = WorkerClient::new(args.api_url);
+
+    let response = client.cancel_job(&args.job_id).await?;
+
+    println!("✓ {}", response.message);
+    println!("  Job ID: {}", response.job_id);
+    println!("  Status: {}", response.status);
+
+    Ok(())
+}
@@ -3,13 +3,18 @@
 use anyhow::Result;
 use prettytable::{Table, row, cell};
 use super::commands::CapabilitiesArgs;
-use super::client::WorkerClient;
+use guestkit_client::{CapabilitiesResponse, WorkerClient};
+use crate::{discovery::CapabilityProbe, handlers, plugins, HandlerRegistry};
+use guestkit_job_spec::ProtocolVersion;
 
 pub async fn run_capabilities(args: CapabilitiesArgs) -> Result<()> {
-    let client = WorkerClient::new(args.api_url);
-
-    // Fetch capabilities
-    let response = client.get_capabilities().await?;
+    let response = if args.local {
+        probe_local(&args)?
+    } else {
+        // Fetch capabilities
+        let client = WorkerClient::new(args.api_url);
+        client.get_capabilities().await?
+    };
 
     match args.output.as_str() {
         "json" => {
@@ -27,6 +32,16 @@ pub async fn run_capabilities(args: CapabilitiesArgs) -> Result<()> {
                 table.add_row(row!["Pool", pool]);
             }
 
+            if response.cpu_cores > 0 {
+                table.add_row(row!["CPU Cores", response.cpu_cores]);
+            }
+            if response.memory_gb > 0 {
+                table.add_row(row!["Memory", format!("{} GB", response.memory_gb)]);
+            }
+            if response.max_disk_size_gb > 0 {
+                table.add_row(row!["Max Disk Size", format!("{} GB", response.max_disk_size_gb)]);
+            }
+
             table.printstd();
 
             // Operations
@@ -46,8 +61,45 @@ pub async fn run_capabilities(args: CapabilitiesArgs) -> Result<()> {
             for format in &response.disk_formats {
                 println!("  • {}", format);
             }
+
+            // Protocol versions
+            if !response.protocol_versions.is_empty() {
+                println!("\nProtocol Versions: {}", response.protocol_versions.join(", "));
+            }
         }
     }
 
     Ok(())
 }
+
+/// Discover what a worker started on this host would advertise
+fn probe_local(args: &CapabilitiesArgs) -> Result<CapabilitiesResponse> {
+    let mut registry = HandlerRegistry::new();
+    handlers::register_builtin(&mut registry);
+    let loaded_plugins = match args.plugin_dir {
+        Some(ref plugin_dir) => plugins::load_plugins(plugin_dir, &mut registry)?,
+        None => Vec::new(),
+    };
+
+    let mut capabilities = CapabilityProbe::new(&args.work_dir).discover(&registry);
+    for feature in loaded_plugins.iter().flat_map(|plugin| plugin.features.iter()) {
+        if !capabilities.has_feature(feature) {
+            capabilities = capabilities.with_feature(feature);
+        }
+    }
+
+    Ok(CapabilitiesResponse {
+        worker_id: "local".to_string(),
+        pool: None,
+        operations: capabilities.operations,
+        features: capabilities.features,
+        disk_formats: capabilities.disk_formats,
+        protocol_versions: ProtocolVersion::SUPPORTED
+            .iter()
+            .map(ToString::to_string)
+            .collect(),
+        max_disk_size_gb: capabilities.max_disk_size_gb,
+        cpu_cores: capabilities.cpu_cores,
+        memory_gb: capabilities.memory_gb,
+    })
+}
@@ -2,6 +2,7 @@
 
 use clap::{Parser, Subcommand};
 use std::path::PathBuf;
+use crate::concurrency::ConcurrencyClass;
 
 /// Guestkit Worker - Distributed job processing system
 #[derive(Parser, Debug)]
@@ -27,6 +28,15 @@ pub enum Commands {
     /// Get job result
     Result(ResultArgs),
 
+    /// Cancel a queued or running job
+    Cancel(CancelArgs),
+
+    /// Pause a running job at its next checkpoint
+    Pause(PauseArgs),
+
+    /// Resume a paused job
+    Resume(ResumeArgs),
+
     /// List all jobs
     List(ListArgs),
 
@@ -60,10 +70,40 @@ pub struct DaemonArgs {
     #[arg(short, long, default_value = "./results")]
     pub results_dir: PathBuf,
 
+    /// Directory for persisted recurring job schedules
+    #[arg(long, default_value = "./schedules")]
+    pub schedule_dir: PathBuf,
+
+    /// Directory for persisted job state (unfinished jobs survive restarts)
+    #[arg(long, default_value = "./state")]
+    pub state_dir: PathBuf,
+
     /// Maximum concurrent jobs
     #[arg(short, long, default_value = "4")]
     pub max_concurrent: usize,
 
+    /// Fail jobs whose dependency has not been seen by the worker after
+    /// this many seconds
+    #[arg(long, default_value = "600")]
+    pub dependency_timeout: u64,
+
+    /// Limit operations to fewer concurrent jobs, as NAME=LIMIT[:OPERATION,...]
+    /// (repeatable; replaces the default of one guestkit.convert at a time)
+    #[arg(long = "concurrency-class", value_name = "CLASS")]
+    pub concurrency_classes: Vec<ConcurrencyClass>,
+
+    /// Largest disk this worker accepts jobs for (GB); 0 means no limit
+    #[arg(long, default_value = "0")]
+    pub max_disk_size_gb: u64,
+
+    /// Advertise a feature discovery cannot detect (repeatable)
+    #[arg(long = "feature", value_name = "FEATURE")]
+    pub features: Vec<String>,
+
+    /// Load handler plugins (shared libraries) from this directory
+    #[arg(long)]
+    pub plugin_dir: Option<PathBuf>,
+
     /// Log level
     #[arg(long, default_value = "info")]
     pub log_level: String,
@@ -87,6 +127,53 @@ pub struct DaemonArgs {
     /// Transport mode: file or http
     #[arg(long, default_value = "file")]
     pub transport: String,
+
+    /// Directory for downloaded remote images (defaults to <work-dir>/scratch)
+    #[arg(long)]
+    pub scratch_dir: Option<PathBuf>,
+
+    /// Upload job outputs under this prefix (s3://bucket/prefix or https://host/path)
+    #[arg(long)]
+    pub upload_url: Option<String>,
+
+    /// S3-compatible endpoint URL (e.g. MinIO)
+    #[arg(long)]
+    pub s3_endpoint: Option<String>,
+
+    /// S3 region used for request signing
+    #[arg(long, default_value = "us-east-1")]
+    pub s3_region: String,
+
+    /// Keep downloaded images after jobs finish
+    #[arg(long)]
+    pub keep_downloads: bool,
+
+    /// Shared directory for job leases; set it on every worker that shares a
+    /// job source so each job runs on exactly one of them
+    #[arg(long)]
+    pub lease_dir: Option<PathBuf>,
+
+    /// Seconds a job lease stays valid without renewal
+    #[arg(long, default_value = "30")]
+    pub lease_ttl: u64,
+
+    /// JWK set of keys trusted to sign jobs; signed jobs that do not verify
+    /// against it are rejected
+    #[arg(long)]
+    pub trust_anchors: Option<PathBuf>,
+
+    /// Reject unsigned jobs too (requires --trust-anchors)
+    #[arg(long, requires = "trust_anchors")]
+    pub require_signed_jobs: bool,
+
+    /// Export job spans to this OTLP/HTTP collector (e.g. http://localhost:4318);
+    /// requires the `otel` feature
+    #[arg(long)]
+    pub otel_endpoint: Option<String>,
+
+    /// Service name reported with exported spans
+    #[arg(long, default_value = "guestkit-worker")]
+    pub otel_service_name: String,
 }
 
 /// Submit command arguments
@@ -119,6 +206,19 @@ pub struct SubmitArgs {
     /// Output format: json, yaml, or table
     #[arg(long, default_value = "table")]
     pub output: String,
+
+    /// Record the submission as a span on this OTLP/HTTP collector and pass
+    /// its trace context on to the worker; requires the `otel` feature
+    #[arg(long)]
+    pub otel_endpoint: Option<String>,
+
+    /// Sign the job with this PKCS#8 private key (Ed25519 or RSA, PEM)
+    #[arg(long, requires = "key_id")]
+    pub sign_key: Option<PathBuf>,
+
+    /// Key ID written into the signature, matched against the worker's trust anchors
+    #[arg(long)]
+    pub key_id: Option<String>,
 }
 
 /// Status command arguments
@@ -159,6 +259,39 @@ pub struct ResultArgs {
     pub save: Option<PathBuf>,
 }
 
+/// Cancel command arguments
+#[derive(Parser, Debug)]
+pub struct CancelArgs {
+    /// Job ID to cancel
+    pub job_id: String,
+
+    /// API server URL
+    #[arg(long, default_value = "http://localhost:8080")]
+    pub api_url: String,
+}
+
+/// Pause command arguments
+#[derive(Parser, Debug)]
+pub struct PauseArgs {
+    /// Job ID to pause
+    pub job_id: String,
+
+    /// API server URL
+    #[arg(long, default_value = "http://localhost:8080")]
+    pub api_url: String,
+}
+
+/// Resume command arguments
+#[derive(Parser, Debug)]
+pub struct ResumeArgs {
+    /// Job ID to resume
+    pub job_id: String,
+
+    /// API server URL
+    #[arg(long, default_value = "http://localhost:8080")]
+    pub api_url: String,
+}
+
 /// List command arguments
 #[derive(Parser, Debug)]
 pub struct ListArgs {
@@ -186,6 +319,18 @@ pub struct CapabilitiesArgs {
     #[arg(long, default_value = "http://localhost:8080")]
     pub api_url: String,
 
+    /// Probe this host instead of asking a running worker
+    #[arg(long)]
+    pub local: bool,
+
+    /// Working directory probed for large-disk support (with --local)
+    #[arg(long, default_value = "/tmp/guestkit-worker")]
+    pub work_dir: PathBuf,
+
+    /// Plugin directory the worker loads handlers from (with --local)
+    #[arg(long)]
+    pub plugin_dir: Option<PathBuf>,
+
     /// Output format: json, yaml, or table
     #[arg(long, default_value = "table")]
     pub output: String,
@@ -3,15 +3,20 @@
 use anyhow::Result;
 use std::sync::Arc;
 use crate::{
-    Worker, WorkerConfig, HandlerRegistry,
-    handlers::{EchoHandler, InspectHandler, ProfileHandler},
+    ArtifactConfig, LeaseConfig, Worker, WorkerConfig, HandlerRegistry,
+    handlers,
+    plugins,
     transport::file::{FileTransport, FileTransportConfig},
     transport::http::{HttpTransport, HttpTransportConfig},
-    capabilities::Capabilities,
+    concurrency::ConcurrencyClass,
+    discovery::CapabilityProbe,
     metrics::MetricsRegistry,
     metrics_server::{MetricsServer, MetricsServerConfig},
     api::server::{ApiServer, ApiServerConfig},
     api::handlers::ApiState,
+    events::EventBus,
+    cancel::CancelRegistry,
+    telemetry,
 };
 use super::commands::DaemonArgs;
 
@@ -24,47 +29,94 @@ pub async fn run_daemon(args: DaemonArgs) -> Result<()> {
 
     log::info!("Starting guestkit worker daemon");
 
+    if let Some(ref endpoint) = args.otel_endpoint {
+        telemetry::init(endpoint, &args.otel_service_name)?;
+        log::info!("Exporting traces to {}", endpoint);
+    }
+
     // Worker configuration
     let config = WorkerConfig {
         worker_id: args.worker_id.clone().unwrap_or_else(|| format!("worker-{}", ulid::Ulid::new())),
         worker_pool: Some(args.pool.clone()),
         work_dir: args.work_dir.clone(),
         result_dir: args.results_dir.clone(),
+        schedule_dir: args.schedule_dir.clone(),
+        state_dir: args.state_dir.clone(),
         max_concurrent_jobs: args.max_concurrent,
+        concurrency_classes: if args.concurrency_classes.is_empty() {
+            ConcurrencyClass::defaults()
+        } else {
+            args.concurrency_classes.clone()
+        },
         shutdown_timeout_secs: 30,
+        dependency_timeout_secs: args.dependency_timeout,
+        artifacts: ArtifactConfig {
+            scratch_dir: args.scratch_dir.clone(),
+            upload_url: args.upload_url.clone(),
+            s3_endpoint: args.s3_endpoint.clone(),
+            s3_region: args.s3_region.clone(),
+            keep_downloads: args.keep_downloads,
+        },
+        lease: LeaseConfig {
+            dir: args.lease_dir.clone(),
+            ttl_secs: args.lease_ttl,
+        },
+        trust_anchors: args.trust_anchors.clone(),
+        require_signed_jobs: args.require_signed_jobs,
     };
 
     log::info!("Worker ID: {}", config.worker_id);
     log::info!("Working directory: {}", config.work_dir.display());
     log::info!("Results directory: {}", config.result_dir.display());
+    log::info!("Schedule directory: {}", config.schedule_dir.display());
+    log::info!("State directory: {}", config.state_dir.display());
+    if let Some(ref lease_dir) = config.lease.dir {
+        log::info!("Lease directory: {}", lease_dir.display());
+    }
+    if let Some(ref trust_anchors) = config.trust_anchors {
+        log::info!(
+            "Verifying job signatures against {}{}",
+            trust_anchors.display(),
+            if config.require_signed_jobs { " (unsigned jobs rejected)" } else { "" }
+        );
+    }
+    if let Some(ref upload_url) = config.artifacts.upload_url {
+        log::info!("Uploading job outputs to: {}", upload_url);
+    }
 
     // Setup handler registry
     let mut registry = HandlerRegistry::new();
 
-    // Register built-in handlers
-    registry.register(Arc::new(EchoHandler::new()));
+    // Register built-in and guestkit operation handlers
+    handlers::register_builtin(&mut registry);
 
-    // Register guestkit operation handlers
-    registry.register(Arc::new(InspectHandler::new()));
-    registry.register(Arc::new(ProfileHandler::new()));
+    // Register plugin handlers; built-in operations take precedence
+    let loaded_plugins = match args.plugin_dir {
+        Some(ref plugin_dir) => plugins::load_plugins(plugin_dir, &mut registry)?,
+        None => Vec::new(),
+    };
 
     log::info!("Registered {} operation handlers", registry.len());
     log::info!("Supported operations: {:?}", registry.operations());
 
-    // Worker capabilities
-    let capabilities = Capabilities::new()
-        .with_operation("system.echo")
-        .with_operation("test.echo")
-        .with_operation("guestkit.inspect")
-        .with_operation("guestkit.profile")
-        .with_feature("rust")
-        .with_feature("lvm")
-        .with_feature("nbd")
-        .with_disk_format("qcow2")
-        .with_disk_format("vmdk")
-        .with_disk_format("vdi")
-        .with_disk_format("vhdx")
-        .with_disk_format("raw");
+    // Worker capabilities, probed from the host
+    let mut capabilities = CapabilityProbe::new(&config.work_dir)
+        .discover(&registry)
+        .with_max_disk_size_gb(args.max_disk_size_gb);
+    let plugin_features = loaded_plugins.iter().flat_map(|plugin| plugin.features.iter());
+    for feature in args.features.iter().chain(plugin_features) {
+        if !capabilities.has_feature(feature) {
+            capabilities = capabilities.with_feature(feature);
+        }
+    }
+
+    log::info!("Disk formats: {:?}", capabilities.disk_formats);
+    log::info!("Features: {:?}", capabilities.features);
+    log::info!(
+        "Resources: {} CPU cores, {} GB memory",
+        capabilities.cpu_cores,
+        capabilities.memory_gb
+    );
 
     // Create metrics registry
     let metrics = Arc::new(MetricsRegistry::new());
@@ -95,6 +147,8 @@ pub async fn run_daemon(args: DaemonArgs) -> Result<()> {
             log::info!("Using HTTP transport with REST API");
 
             let http_transport = HttpTransport::new(HttpTransportConfig::default());
+            let event_bus = EventBus::new();
+            let cancellations = CancelRegistry::new();
 
             // Start API server if enabled
             let _api_handle = if args.api_enabled {
@@ -108,6 +162,8 @@ pub async fn run_daemon(args: DaemonArgs) -> Result<()> {
                     capabilities: capabilities.clone(),
                     job_submitter: http_transport.get_submitter(),
                     job_status_lookup: http_transport.get_status_lookup(),
+                    job_canceller: Arc::new(cancellations.clone()),
+                    event_bus: event_bus.clone(),
                 };
 
                 let server = ApiServer::new(api_config.clone(), api_state);
@@ -118,7 +174,11 @@ pub async fn run_daemon(args: DaemonArgs) -> Result<()> {
                 log::info!("  POST   http://{}/api/v1/jobs", api_config.bind_addr);
                 log::info!("  GET    http://{}/api/v1/jobs", api_config.bind_addr);
                 log::info!("  GET    http://{}/api/v1/jobs/:id", api_config.bind_addr);
+                log::info!("  DELETE http://{}/api/v1/jobs/:id", api_config.bind_addr);
+                log::info!("  POST   http://{}/api/v1/jobs/:id/pause", api_config.bind_addr);
+                log::info!("  POST   http://{}/api/v1/jobs/:id/resume", api_config.bind_addr);
                 log::info!("  GET    http://{}/api/v1/jobs/:id/result", api_config.bind_addr);
+                log::info!("  GET    http://{}/api/v1/jobs/:id/events", api_config.bind_addr);
                 log::info!("  GET    http://{}/api/v1/capabilities", api_config.bind_addr);
                 log::info!("  GET    http://{}/api/v1/health", api_config.bind_addr);
 
@@ -137,6 +197,8 @@ pub async fn run_daemon(args: DaemonArgs) -> Result<()> {
             )?;
 
             worker.with_metrics(metrics);
+            worker.with_event_bus(event_bus);
+            worker.with_cancel_registry(cancellations);
 
             log::info!("Worker ready, waiting for jobs...");
             worker.run().await?;
@@ -170,6 +232,10 @@ pub async fn run_daemon(args: DaemonArgs) -> Result<()> {
         }
     }
 
+    if args.otel_endpoint.is_some() {
+        tokio::task::spawn_blocking(telemetry::shutdown).await?;
+    }
+
     log::info!("Worker shut down cleanly");
 
     Ok(())
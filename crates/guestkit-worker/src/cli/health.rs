@@ -3,7 +3,7 @@
 use anyhow::Result;
 use prettytable::{Table, row};
 use super::commands::HealthArgs;
-use super::client::WorkerClient;
+use guestkit_client::WorkerClient;
 
 pub async fn run_health(args: HealthArgs) -> Result<()> {
     let client = WorkerClient::new(args.api_url);
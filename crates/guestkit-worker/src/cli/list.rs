@@ -3,7 +3,7 @@
 use anyhow::Result;
 use prettytable::{Table, row};
 use super::commands::ListArgs;
-use super::client::WorkerClient;
+use guestkit_client::WorkerClient;
 
 pub async fn run_list(args: ListArgs) -> Result<()> {
     let client = WorkerClient::new(args.api_url);
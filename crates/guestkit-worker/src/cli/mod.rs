@@ -1,11 +1,12 @@
 //! CLI module for guestkit-worker
 
-pub mod client;
 pub mod commands;
 pub mod daemon;
 pub mod submit;
 pub mod status;
 pub mod result;
+pub mod cancel;
+pub mod pause;
 pub mod list;
 pub mod capabilities;
 pub mod health;
@@ -23,6 +24,9 @@ pub async fn run() -> Result<()> {
         Commands::Submit(args) => submit::run_submit(args).await,
         Commands::Status(args) => status::run_status(args).await,
         Commands::Result(args) => result::run_result(args).await,
+        Commands::Cancel(args) => cancel::run_cancel(args).await,
+        Commands::Pause(args) => pause::run_pause(args).await,
+        Commands::Resume(args) => pause::run_resume(args).await,
         Commands::List(args) => list::run_list(args).await,
         Commands::Capabilities(args) => capabilities::run_capabilities(args).await,
         Commands::Health(args) => health::run_health(args).await,
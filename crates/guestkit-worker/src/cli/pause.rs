@@ -0,0 +1,29 @@
+//! Pause and resume command handlers
+
+use anyhow::Result;
+use super::commands::{PauseArgs, ResumeArgs};
+use guestkit_client::WorkerClient;
+
+pub async fn run_pause(args: PauseArgs) -> Result<()> {
+    let client = WorkerClient::new(args.api_url);
+
+    let response = client.pause_job(&args.job_id).await?;
+
+    println!("✓ {}", response.message);
+    println!("  Job ID: {}", response.job_id);
+    println!("  Status: {}", response.status);
+
+    Ok(())
+}
+
+pub async fn run_resume(args: ResumeArgs) -> Result<()> {
+    let client = WorkerClient::new(args.api_url);
+
+    let response = client.resume_job(&args.job_id).await?;
+
+    println!("✓ {}", response.message);
+    println!("  Job ID: {}", response.job_id);
+    println!("  Status: {}", response.status);
+
+    Ok(())
+}
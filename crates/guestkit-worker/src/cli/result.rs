@@ -3,7 +3,7 @@
 use anyhow::{Result, Context};
 use std::fs;
 use super::commands::ResultArgs;
-use super::client::WorkerClient;
+use guestkit_client::WorkerClient;
 
 pub async fn run_result(args: ResultArgs) -> Result<()> {
     let client = WorkerClient::new(args.api_url);
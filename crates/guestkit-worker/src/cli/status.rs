@@ -3,7 +3,7 @@
 use anyhow::Result;
 use prettytable::{Table, row};
 use super::commands::StatusArgs;
-use super::client::WorkerClient;
+use guestkit_client::WorkerClient;
 
 pub async fn run_status(args: StatusArgs) -> Result<()> {
     let client = WorkerClient::new(args.api_url);
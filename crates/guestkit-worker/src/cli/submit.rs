@@ -1,17 +1,18 @@
 //! Submit command handler
 
 use anyhow::{Result, Context, bail};
-use guestkit_job_spec::{JobDocument, JobBuilder};
+use guestkit_job_spec::{JobDocument, JobBuilder, JobSigner};
 use serde_json::json;
 use std::fs;
 use std::path::PathBuf;
 use prettytable::{Table, row};
 use super::commands::SubmitArgs;
-use super::client::WorkerClient;
+use guestkit_client::WorkerClient;
+use crate::telemetry::{self, TraceContext};
 
 pub async fn run_submit(args: SubmitArgs) -> Result<()> {
     // Create job document from args
-    let job = if let Some(file_path) = args.file {
+    let mut job = if let Some(file_path) = args.file {
         // Load from file
         load_job_from_file(&file_path)?
     } else if let Some(json_str) = args.json {
@@ -30,12 +31,44 @@ pub async fn run_submit(args: SubmitArgs) -> Result<()> {
         bail!("Job operation cannot be empty");
     }
 
+    // Start the trace the worker's spans will join
+    let span = match args.otel_endpoint {
+        Some(ref endpoint) => {
+            telemetry::init(endpoint, "guestkit-worker-cli")?;
+            let span = TraceContext::from_job(&job).span("job.submit");
+            span.set_attribute("job.id", job.job_id.clone());
+            if let Some(ids) = span.context().observability() {
+                let obs = job.observability.get_or_insert_with(Default::default);
+                obs.parent_span_id = obs.span_id.take();
+                obs.trace_id = ids.trace_id;
+                obs.span_id = ids.span_id;
+            }
+            Some(span)
+        }
+        None => None,
+    };
+
+    // Sign last, so the signature covers the trace context too
+    if let (Some(ref key_path), Some(ref key_id)) = (args.sign_key, args.key_id) {
+        let pem = fs::read_to_string(key_path)
+            .with_context(|| format!("Failed to read signing key: {}", key_path.display()))?;
+        JobSigner::from_pkcs8_pem(key_id.as_str(), &pem)?.sign(&mut job)?;
+    }
+
     // Create API client
     let client = WorkerClient::new(args.api_url);
 
     // Submit job
     println!("Submitting job...");
-    let response = client.submit_job(job).await?;
+    let submitted = client.submit_job(job).await;
+    if let Some(span) = span {
+        if let Err(ref e) = submitted {
+            span.record_error(e);
+        }
+        drop(span);
+        tokio::task::spawn_blocking(telemetry::shutdown).await?;
+    }
+    let response = submitted?;
 
     // Output response
     match args.output.as_str() {
@@ -68,13 +101,15 @@ fn load_job_from_file(path: &PathBuf) -> Result<JobDocument> {
     let content = fs::read_to_string(path)
         .with_context(|| format!("Failed to read job file: {}", path.display()))?;
 
-    // Try JSON first, then YAML
-    if let Ok(job) = serde_json::from_str::<JobDocument>(&content) {
-        return Ok(job);
-    }
+    // Try JSON first, then YAML; older protocol versions are upconverted
+    let value = match serde_json::from_str::<serde_json::Value>(&content) {
+        Ok(value) => value,
+        Err(_) => serde_yaml::from_str(&content)
+            .context("Failed to parse job file as JSON or YAML")?,
+    };
 
-    serde_yaml::from_str(&content)
-        .context("Failed to parse job file as JSON or YAML")
+    JobDocument::from_value_any(value)
+        .with_context(|| format!("Invalid job file: {}", path.display()))
 }
 
 fn create_quick_job(operation: &str, image: Option<PathBuf>) -> Result<JobDocument> {
@@ -89,7 +124,7 @@ fn create_quick_job(operation: &str, image: Option<PathBuf>) -> Result<JobDocume
     let job = JobBuilder::new()
         .generate_job_id()
         .operation(operation)
-        .payload(operation, data)
+        .payload(format!("{}.v1", operation), data)
         .build()?;
 
     Ok(job)
@@ -0,0 +1,434 @@
+//! Job concurrency control
+//!
+//! Bounds how many jobs run at once, both overall and per concurrency class
+//! (a group of operations sharing a limit, e.g. one disk conversion at a
+//! time while many inspections run). Free slots are handed to tenants in
+//! turn, so one namespace submitting a burst of jobs cannot starve the
+//! others.
+
+use std::collections::{BTreeMap, HashMap, VecDeque};
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+use tokio::sync::oneshot;
+
+/// Tenant of jobs without a namespace
+pub const DEFAULT_TENANT: &str = "default";
+
+/// Operations sharing a concurrency limit
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ConcurrencyClass {
+    /// Class name
+    pub name: String,
+
+    /// Operations in the class
+    pub operations: Vec<String>,
+
+    /// Jobs of the class that may run at once
+    pub limit: usize,
+}
+
+impl ConcurrencyClass {
+    /// Create an empty class
+    pub fn new(name: impl Into<String>, limit: usize) -> Self {
+        Self {
+            name: name.into(),
+            operations: Vec::new(),
+            limit,
+        }
+    }
+
+    /// Add an operation to the class
+    pub fn with_operation(mut self, operation: impl Into<String>) -> Self {
+        self.operations.push(operation.into());
+        self
+    }
+
+    /// Classes used unless configured otherwise
+    ///
+    /// Conversions read and write whole disks, so running several at once
+    /// mostly makes each of them slower.
+    pub fn defaults() -> Vec<Self> {
+        vec![Self::new("convert", 1).with_operation("guestkit.convert")]
+    }
+}
+
+impl FromStr for ConcurrencyClass {
+    type Err = String;
+
+    /// Parse `NAME=LIMIT[:OPERATION,...]`; without operations the class
+    /// holds the operation called `NAME`
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, rest) = s
+            .split_once('=')
+            .ok_or_else(|| format!("expected NAME=LIMIT[:OPERATION,...], got '{}'", s))?;
+        let (limit, operations) = match rest.split_once(':') {
+            Some((limit, operations)) => (limit, Some(operations)),
+            None => (rest, None),
+        };
+
+        let name = name.trim();
+        if name.is_empty() {
+            return Err(format!("missing class name in '{}'", s));
+        }
+        let limit: usize = limit
+            .trim()
+            .parse()
+            .map_err(|_| format!("invalid limit '{}' in '{}'", limit, s))?;
+        if limit == 0 {
+            return Err(format!("limit of class '{}' must be at least 1", name));
+        }
+
+        let operations = match operations {
+            Some(operations) => operations
+                .split(',')
+                .map(str::trim)
+                .filter(|op| !op.is_empty())
+                .map(str::to_string)
+                .collect(),
+            None => vec![name.to_string()],
+        };
+
+        Ok(Self {
+            name: name.to_string(),
+            operations,
+            limit,
+        })
+    }
+}
+
+/// Hands out execution slots to queued jobs
+pub struct ConcurrencyLimiter {
+    state: Arc<Mutex<LimiterState>>,
+}
+
+impl ConcurrencyLimiter {
+    /// Create a limiter running at most `max_jobs` jobs at once
+    pub fn new(max_jobs: usize, classes: Vec<ConcurrencyClass>) -> Self {
+        Self {
+            state: Arc::new(Mutex::new(LimiterState {
+                max_jobs: max_jobs.max(1),
+                classes,
+                running: 0,
+                running_by_class: HashMap::new(),
+                queues: BTreeMap::new(),
+                last_tenant: None,
+            })),
+        }
+    }
+
+    /// Wait for a slot to run an `operation` job of `tenant`
+    ///
+    /// The slot is held until the permit is dropped. Dropping the future
+    /// while waiting gives up the place in the queue.
+    pub async fn acquire(&self, tenant: &str, operation: &str) -> SlotPermit {
+        let rx = {
+            let mut state = self.state.lock().unwrap();
+            let class = state.class_of(operation);
+            let (tx, rx) = oneshot::channel();
+            state
+                .queues
+                .entry(tenant.to_string())
+                .or_default()
+                .push_back(Waiter { class, tx });
+            state.dispatch(&self.state);
+            rx
+        };
+
+        rx.await
+            .expect("concurrency limiter dropped while a job was waiting")
+    }
+
+    /// Jobs holding a slot
+    pub fn running(&self) -> usize {
+        self.state.lock().unwrap().running
+    }
+
+    /// Jobs waiting for a slot
+    pub fn waiting(&self) -> usize {
+        self.state.lock().unwrap().waiting()
+    }
+
+    /// Check if fetching more jobs would only grow the queue
+    ///
+    /// True when every slot is taken or a full round of jobs is already
+    /// waiting.
+    pub fn is_saturated(&self) -> bool {
+        let state = self.state.lock().unwrap();
+        state.running >= state.max_jobs || state.waiting() >= state.max_jobs
+    }
+
+    /// Name of the concurrency class of `operation`
+    pub fn class_of(&self, operation: &str) -> Option<String> {
+        self.state.lock().unwrap().class_of(operation)
+    }
+}
+
+/// Execution slot held by a running job
+pub struct SlotPermit {
+    /// Limiter the slot is returned to (unset once returned)
+    state: Option<Arc<Mutex<LimiterState>>>,
+
+    /// Concurrency class the slot counts against
+    class: Option<String>,
+}
+
+impl Drop for SlotPermit {
+    fn drop(&mut self) {
+        if let Some(handle) = self.state.take() {
+            let mut state = handle.lock().unwrap();
+            state.release(self.class.as_deref());
+            state.dispatch(&handle);
+        }
+    }
+}
+
+/// A job waiting for a slot
+struct Waiter {
+    class: Option<String>,
+    tx: oneshot::Sender<SlotPermit>,
+}
+
+struct LimiterState {
+    max_jobs: usize,
+    classes: Vec<ConcurrencyClass>,
+    running: usize,
+    running_by_class: HashMap<String, usize>,
+
+    /// Waiting jobs per tenant, in arrival order
+    queues: BTreeMap<String, VecDeque<Waiter>>,
+
+    /// Tenant that got the last slot
+    last_tenant: Option<String>,
+}
+
+impl LimiterState {
+    fn class_of(&self, operation: &str) -> Option<String> {
+        self.classes
+            .iter()
+            .find(|class| class.operations.iter().any(|op| op == operation))
+            .map(|class| class.name.clone())
+    }
+
+    fn waiting(&self) -> usize {
+        self.queues
+            .values()
+            .flatten()
+            .filter(|waiter| !waiter.tx.is_closed())
+            .count()
+    }
+
+    fn class_has_room(&self, class: Option<&str>) -> bool {
+        let Some(name) = class else {
+            return true;
+        };
+        let limit = self
+            .classes
+            .iter()
+            .find(|c| c.name == name)
+            .map(|c| c.limit)
+            .unwrap_or(usize::MAX);
+        self.running_by_class.get(name).copied().unwrap_or(0) < limit
+    }
+
+    fn release(&mut self, class: Option<&str>) {
+        self.running -= 1;
+        if let Some(name) = class {
+            if let Some(count) = self.running_by_class.get_mut(name) {
+                *count -= 1;
+            }
+        }
+    }
+
+    /// Grant free slots to waiting jobs
+    fn dispatch(&mut self, handle: &Arc<Mutex<LimiterState>>) {
+        // Forget jobs that stopped waiting
+        for queue in self.queues.values_mut() {
+            queue.retain(|waiter| !waiter.tx.is_closed());
+        }
+        self.queues.retain(|_, queue| !queue.is_empty());
+
+        while self.running < self.max_jobs {
+            let Some((tenant, index)) = self.next_admissible() else {
+                return;
+            };
+
+            let queue = self.queues.get_mut(&tenant).expect("tenant has a queue");
+            let waiter = queue.remove(index).expect("waiter is queued");
+            if queue.is_empty() {
+                self.queues.remove(&tenant);
+            }
+            self.last_tenant = Some(tenant);
+
+            self.running += 1;
+            if let Some(ref name) = waiter.class {
+                *self.running_by_class.entry(name.clone()).or_insert(0) += 1;
+            }
+
+            let permit = SlotPermit {
+                state: Some(Arc::clone(handle)),
+                class: waiter.class,
+            };
+            if let Err(mut permit) = waiter.tx.send(permit) {
+                // The job stopped waiting after the queue was pruned
+                permit.state = None;
+                self.release(permit.class.as_deref());
+            }
+        }
+    }
+
+    /// First job whose class has room, visiting tenants round-robin
+    /// starting after the one served last
+    fn next_admissible(&self) -> Option<(String, usize)> {
+        let (later, earlier): (Vec<_>, Vec<_>) =
+            self.queues
+                .iter()
+                .partition(|(tenant, _)| match self.last_tenant {
+                    Some(ref last) => *tenant > last,
+                    None => true,
+                });
+
+        later
+            .into_iter()
+            .chain(earlier)
+            .find_map(|(tenant, queue)| {
+                queue
+                    .iter()
+                    .position(|waiter| self.class_has_room(waiter.class.as_deref()))
+                    .map(|index| (tenant.clone(), index))
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    async fn acquire_now(
+        limiter: &ConcurrencyLimiter,
+        tenant: &str,
+        operation: &str,
+    ) -> Option<SlotPermit> {
+        tokio::time::timeout(
+            Duration::from_millis(50),
+            limiter.acquire(tenant, operation),
+        )
+        .await
+        .ok()
+    }
+
+    #[test]
+    fn test_parse_class() {
+        let class: ConcurrencyClass = "guestkit.convert=1".parse().unwrap();
+        assert_eq!(class.name, "guestkit.convert");
+        assert_eq!(class.operations, vec!["guestkit.convert".to_string()]);
+        assert_eq!(class.limit, 1);
+
+        let class: ConcurrencyClass = "disk-write=2:guestkit.convert, guestkit.fix"
+            .parse()
+            .unwrap();
+        assert_eq!(class.name, "disk-write");
+        assert_eq!(
+            class.operations,
+            vec!["guestkit.convert".to_string(), "guestkit.fix".to_string()]
+        );
+        assert_eq!(class.limit, 2);
+
+        assert!("convert".parse::<ConcurrencyClass>().is_err());
+        assert!("convert=0".parse::<ConcurrencyClass>().is_err());
+        assert!("convert=many".parse::<ConcurrencyClass>().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_class_limit() {
+        let limiter = ConcurrencyLimiter::new(4, ConcurrencyClass::defaults());
+
+        let convert = acquire_now(&limiter, "a", "guestkit.convert")
+            .await
+            .unwrap();
+        assert!(acquire_now(&limiter, "a", "guestkit.convert")
+            .await
+            .is_none());
+
+        // Other operations still run next to the conversion
+        let inspect1 = acquire_now(&limiter, "a", "guestkit.inspect")
+            .await
+            .unwrap();
+        let inspect2 = acquire_now(&limiter, "a", "guestkit.inspect")
+            .await
+            .unwrap();
+        assert_eq!(limiter.running(), 3);
+
+        drop(convert);
+        assert!(acquire_now(&limiter, "a", "guestkit.convert")
+            .await
+            .is_some());
+        drop((inspect1, inspect2));
+        assert_eq!(limiter.running(), 0);
+        assert_eq!(limiter.waiting(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_global_limit() {
+        let limiter = Arc::new(ConcurrencyLimiter::new(1, Vec::new()));
+
+        let first = limiter.acquire("a", "guestkit.inspect").await;
+        let waiting = {
+            let limiter = Arc::clone(&limiter);
+            tokio::spawn(async move { limiter.acquire("a", "guestkit.inspect").await })
+        };
+        tokio::time::sleep(Duration::from_millis(20)).await;
+        assert_eq!(limiter.waiting(), 1);
+        assert!(limiter.is_saturated());
+
+        drop(first);
+        let second = waiting.await.unwrap();
+        assert_eq!(limiter.running(), 1);
+        drop(second);
+        assert!(!limiter.is_saturated());
+    }
+
+    #[tokio::test]
+    async fn test_tenants_take_turns() {
+        let limiter = Arc::new(ConcurrencyLimiter::new(1, Vec::new()));
+        let order = Arc::new(Mutex::new(Vec::new()));
+
+        let blocker = limiter.acquire("a", "op").await;
+
+        // Tenant a queues three jobs before tenant b queues one
+        let mut tasks = Vec::new();
+        for (i, tenant) in ["a", "a", "a", "b"].into_iter().enumerate() {
+            let limiter = Arc::clone(&limiter);
+            let order = Arc::clone(&order);
+            tasks.push(tokio::spawn(async move {
+                let _permit = limiter.acquire(tenant, "op").await;
+                order.lock().unwrap().push(format!("{}{}", tenant, i));
+            }));
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+
+        drop(blocker);
+        for task in tasks {
+            task.await.unwrap();
+        }
+
+        // a got the last slot, so b goes next although a queued first
+        let order = order.lock().unwrap().clone();
+        assert_eq!(order, vec!["b3", "a0", "a1", "a2"]);
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_wait_frees_queue() {
+        let limiter = ConcurrencyLimiter::new(1, Vec::new());
+
+        let held = limiter.acquire("a", "op").await;
+        assert!(acquire_now(&limiter, "b", "op").await.is_none());
+        assert_eq!(limiter.waiting(), 0);
+
+        drop(held);
+        assert_eq!(limiter.running(), 0);
+        assert!(acquire_now(&limiter, "c", "op").await.is_some());
+    }
+}
@@ -0,0 +1,238 @@
+//! Capability discovery
+//!
+//! Probes the host at startup so the worker advertises what it can actually
+//! do instead of a hand-maintained list: the disk formats qemu-img handles,
+//! the block device backends guestfs can attach images with, whether the
+//! work directory can hold large sparse images, and optional tools such as
+//! YARA.
+
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use guestkit_job_spec::payloads::CONVERT_TARGET_FORMATS;
+
+use crate::capabilities::Capabilities;
+use crate::handler::HandlerRegistry;
+
+/// Size of the sparse file used to probe large-disk support (2 TiB + 1 byte,
+/// past the limit of filesystems with 32-bit block counts)
+const LARGE_DISK_PROBE_BYTES: u64 = (2 << 40) + 1;
+
+/// Optional features backed by a host command
+const COMMAND_FEATURES: [(&str, &str); 4] = [
+    ("lvm", "lvm"),
+    ("luks", "cryptsetup"),
+    ("selinux", "setfiles"),
+    ("yara", "yara"),
+];
+
+/// Probes the host for worker capabilities
+#[derive(Debug, Clone)]
+pub struct CapabilityProbe {
+    /// qemu-img binary
+    qemu_img: PathBuf,
+
+    /// Directories searched for commands (defaults to `PATH`)
+    search_path: Vec<PathBuf>,
+
+    /// Prefix for /dev and /sys lookups
+    root: PathBuf,
+
+    /// Directory where images are staged and converted
+    work_dir: PathBuf,
+}
+
+impl CapabilityProbe {
+    /// Create a probe for a worker using `work_dir`
+    pub fn new(work_dir: impl Into<PathBuf>) -> Self {
+        let search_path = std::env::var_os("PATH")
+            .map(|path| std::env::split_paths(&path).collect())
+            .unwrap_or_default();
+
+        Self {
+            qemu_img: PathBuf::from("qemu-img"),
+            search_path,
+            root: PathBuf::from("/"),
+            work_dir: work_dir.into(),
+        }
+    }
+
+    /// Use a specific qemu-img binary
+    pub fn with_qemu_img(mut self, path: impl Into<PathBuf>) -> Self {
+        self.qemu_img = path.into();
+        self
+    }
+
+    /// Search these directories for commands instead of `PATH`
+    pub fn with_search_path(mut self, dirs: Vec<PathBuf>) -> Self {
+        self.search_path = dirs;
+        self
+    }
+
+    /// Look up /dev and /sys under another root
+    pub fn with_root(mut self, root: impl Into<PathBuf>) -> Self {
+        self.root = root.into();
+        self
+    }
+
+    /// Discover the capabilities of this host
+    ///
+    /// Operations come from the registered handlers; formats, features and
+    /// resources from probing the host.
+    pub fn discover(&self, registry: &HandlerRegistry) -> Capabilities {
+        let mut operations = registry.operations();
+        operations.sort();
+
+        let capabilities = Capabilities {
+            operations,
+            features: self.features(),
+            disk_formats: self.disk_formats(),
+            ..Default::default()
+        };
+
+        capabilities.with_host_resources()
+    }
+
+    /// Disk formats the worker can open and write
+    ///
+    /// Raw images attach through loop devices; everything else needs
+    /// qemu-img support for the format.
+    pub fn disk_formats(&self) -> Vec<String> {
+        let supported = match Command::new(&self.qemu_img).arg("--help").output() {
+            Ok(output) => parse_qemu_img_formats(&String::from_utf8_lossy(&output.stdout)),
+            Err(e) => {
+                log::warn!(
+                    "{} not usable, only raw images are supported: {}",
+                    self.qemu_img.display(),
+                    e
+                );
+                Vec::new()
+            }
+        };
+
+        CONVERT_TARGET_FORMATS
+            .iter()
+            .filter(|format| **format == "raw" || supported.iter().any(|s| s == *format))
+            .map(|format| format.to_string())
+            .collect()
+    }
+
+    /// Optional features available on this host
+    pub fn features(&self) -> Vec<String> {
+        let mut features = vec!["rust".to_string()];
+
+        if self.root.join("dev/loop-control").exists() {
+            features.push("loop".to_string());
+        }
+
+        let nbd_module =
+            self.root.join("sys/module/nbd").exists() || self.root.join("dev/nbd0").exists();
+        if nbd_module && self.has_command("qemu-nbd") {
+            features.push("nbd".to_string());
+        }
+
+        if supports_large_files(&self.work_dir) {
+            features.push("large-disk".to_string());
+        }
+
+        for (feature, command) in COMMAND_FEATURES {
+            if self.has_command(command) {
+                features.push(feature.to_string());
+            }
+        }
+
+        if cfg!(feature = "ai") && std::env::var_os("OPENAI_API_KEY").is_some() {
+            features.push("ai".to_string());
+        }
+
+        features
+    }
+
+    /// Check if a command is on the search path
+    fn has_command(&self, name: &str) -> bool {
+        self.search_path.iter().any(|dir| dir.join(name).is_file())
+    }
+}
+
+/// Parse the format list at the end of `qemu-img --help`
+pub fn parse_qemu_img_formats(help: &str) -> Vec<String> {
+    help.lines()
+        .find_map(|line| line.trim().strip_prefix("Supported formats:"))
+        .map(|formats| formats.split_whitespace().map(str::to_string).collect())
+        .unwrap_or_default()
+}
+
+/// Check if `dir` can hold a sparse file larger than 2 TiB
+fn supports_large_files(dir: &Path) -> bool {
+    if std::fs::create_dir_all(dir).is_err() {
+        return false;
+    }
+
+    let path = dir.join(format!(".large-disk-probe-{}", std::process::id()));
+    let supported = std::fs::File::create(&path)
+        .and_then(|file| file.set_len(LARGE_DISK_PROBE_BYTES))
+        .is_ok();
+    let _ = std::fs::remove_file(&path);
+
+    supported
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_qemu_img_formats() {
+        let help = "qemu-img version 8.2.0\n\
+                    usage: qemu-img [standard options] command [command options]\n\
+                    \n\
+                    Supported formats: blkdebug luks qcow qcow2 raw vdi vhdx vmdk vpc\n\
+                    \n\
+                    See <https://qemu.org/contribute/report-a-bug> for how to report bugs.\n";
+
+        let formats = parse_qemu_img_formats(help);
+        assert!(formats.contains(&"qcow2".to_string()));
+        assert!(formats.contains(&"vpc".to_string()));
+        assert!(parse_qemu_img_formats("no formats here").is_empty());
+    }
+
+    #[test]
+    fn test_probe_without_tools() {
+        let temp_dir = TempDir::new().unwrap();
+        let probe = CapabilityProbe::new(temp_dir.path().join("work"))
+            .with_qemu_img(temp_dir.path().join("missing-qemu-img"))
+            .with_search_path(Vec::new())
+            .with_root(temp_dir.path());
+
+        assert_eq!(probe.disk_formats(), vec!["raw".to_string()]);
+
+        let features = probe.features();
+        assert!(features.contains(&"rust".to_string()));
+        assert!(!features.contains(&"nbd".to_string()));
+        assert!(!features.contains(&"yara".to_string()));
+    }
+
+    #[test]
+    fn test_probe_finds_devices_and_commands() {
+        let temp_dir = TempDir::new().unwrap();
+        let bin = temp_dir.path().join("bin");
+        std::fs::create_dir_all(&bin).unwrap();
+        for command in ["qemu-nbd", "yara", "lvm"] {
+            std::fs::write(bin.join(command), "").unwrap();
+        }
+        std::fs::create_dir_all(temp_dir.path().join("dev")).unwrap();
+        std::fs::write(temp_dir.path().join("dev/loop-control"), "").unwrap();
+        std::fs::create_dir_all(temp_dir.path().join("sys/module/nbd")).unwrap();
+
+        let features = CapabilityProbe::new(temp_dir.path().join("work"))
+            .with_search_path(vec![bin])
+            .with_root(temp_dir.path())
+            .features();
+
+        for feature in ["loop", "nbd", "yara", "lvm"] {
+            assert!(features.contains(&feature.to_string()), "{}", feature);
+        }
+        assert!(!features.contains(&"luks".to_string()));
+    }
+}
@@ -20,12 +20,24 @@ pub enum WorkerError {
     #[error("Capability mismatch: {0}")]
     CapabilityMismatch(String),
 
+    #[error("Constraint not met: {0}")]
+    ConstraintNotMet(crate::capabilities::UnmetConstraint),
+
     #[error("Invalid state transition: {current} -> {target}")]
     InvalidStateTransition { current: String, target: String },
 
     #[error("Job timeout after {seconds} seconds")]
     Timeout { seconds: u64 },
 
+    #[error("Dependency not satisfied: {0}")]
+    DependencyNotMet(String),
+
+    #[error("Job cancelled: {0}")]
+    Cancelled(String),
+
+    #[error("Lease on job {job_id} lost (fencing token {token} superseded)")]
+    LeaseLost { job_id: String, token: u64 },
+
     #[error("Worker shutdown requested")]
     ShutdownRequested,
 
@@ -38,15 +50,49 @@ pub enum WorkerError {
     #[error("File watch error: {0}")]
     WatchError(#[from] notify::Error),
 
+    #[error("Job store error: {0}")]
+    StoreError(#[from] sled::Error),
+
     #[error("Job already exists with idempotency key: {0}")]
     DuplicateIdempotencyKey(String),
 
     #[error("Invalid configuration: {0}")]
     InvalidConfig(String),
 
+    #[error("Plugin error: {0}")]
+    PluginError(String),
+
     #[error(transparent)]
     Other(#[from] anyhow::Error),
 }
 
+impl WorkerError {
+    /// Short machine-readable name of the error variant, used as a metric label
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Self::JobError(_) => "job",
+            Self::TransportError(_) => "transport",
+            Self::ExecutionError(_) => "execution",
+            Self::HandlerNotFound(_) => "handler_not_found",
+            Self::CapabilityMismatch(_) => "capability_mismatch",
+            Self::ConstraintNotMet(_) => "constraint",
+            Self::InvalidStateTransition { .. } => "invalid_state",
+            Self::Timeout { .. } => "timeout",
+            Self::DependencyNotMet(_) => "dependency",
+            Self::Cancelled(_) => "cancelled",
+            Self::LeaseLost { .. } => "lease_lost",
+            Self::ShutdownRequested => "shutdown",
+            Self::IoError(_) => "io",
+            Self::SerializationError(_) => "serialization",
+            Self::WatchError(_) => "watch",
+            Self::StoreError(_) => "store",
+            Self::DuplicateIdempotencyKey(_) => "duplicate",
+            Self::InvalidConfig(_) => "config",
+            Self::PluginError(_) => "plugin",
+            Self::Other(_) => "other",
+        }
+    }
+}
+
 /// Result type alias for worker operations
 pub type WorkerResult<T> = Result<T, WorkerError>;
@@ -0,0 +1,130 @@
+//! Job event broadcasting for real-time streaming
+
+use chrono::{DateTime, Utc};
+use guestkit_job_spec::ProgressEvent;
+use serde::{Deserialize, Serialize};
+use tokio::sync::broadcast;
+use crate::state::JobState;
+
+/// Default number of events buffered per subscriber
+const DEFAULT_CAPACITY: usize = 1024;
+
+/// Event emitted while a job moves through the executor
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum JobEvent {
+    /// Progress reported by a handler
+    Progress(Box<ProgressEvent>),
+
+    /// Job state machine transition
+    StateChanged {
+        job_id: String,
+        from: JobState,
+        to: JobState,
+        timestamp: DateTime<Utc>,
+    },
+}
+
+impl JobEvent {
+    /// Create a state transition event
+    pub fn state_changed(job_id: impl Into<String>, from: JobState, to: JobState) -> Self {
+        JobEvent::StateChanged {
+            job_id: job_id.into(),
+            from,
+            to,
+            timestamp: Utc::now(),
+        }
+    }
+
+    /// Job this event belongs to
+    pub fn job_id(&self) -> &str {
+        match self {
+            JobEvent::Progress(event) => &event.job_id,
+            JobEvent::StateChanged { job_id, .. } => job_id,
+        }
+    }
+
+    /// Event name used on the wire (e.g. SSE `event:` field)
+    pub fn name(&self) -> &'static str {
+        match self {
+            JobEvent::Progress(_) => "progress",
+            JobEvent::StateChanged { .. } => "state",
+        }
+    }
+
+    /// Check if this event ends the job's event stream
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, JobEvent::StateChanged { to, .. } if to.is_terminal())
+    }
+}
+
+/// Fan-out bus delivering job events to any number of subscribers
+#[derive(Debug, Clone)]
+pub struct EventBus {
+    sender: broadcast::Sender<JobEvent>,
+}
+
+impl EventBus {
+    /// Create a new event bus
+    pub fn new() -> Self {
+        Self::with_capacity(DEFAULT_CAPACITY)
+    }
+
+    /// Create a new event bus buffering up to `capacity` events per subscriber
+    pub fn with_capacity(capacity: usize) -> Self {
+        let (sender, _) = broadcast::channel(capacity);
+        Self { sender }
+    }
+
+    /// Publish an event to all current subscribers
+    ///
+    /// Events published while nobody is subscribed are dropped.
+    pub fn publish(&self, event: JobEvent) {
+        let _ = self.sender.send(event);
+    }
+
+    /// Subscribe to events published from now on
+    pub fn subscribe(&self) -> broadcast::Receiver<JobEvent> {
+        self.sender.subscribe()
+    }
+
+    /// Number of active subscribers
+    pub fn subscriber_count(&self) -> usize {
+        self.sender.receiver_count()
+    }
+}
+
+impl Default for EventBus {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_event_bus_fanout() {
+        let bus = EventBus::new();
+        let mut rx1 = bus.subscribe();
+        let mut rx2 = bus.subscribe();
+
+        bus.publish(JobEvent::state_changed("job-1", JobState::Running, JobState::Completed));
+
+        let event1 = rx1.recv().await.unwrap();
+        let event2 = rx2.recv().await.unwrap();
+        assert_eq!(event1.job_id(), "job-1");
+        assert_eq!(event2.name(), "state");
+        assert!(event1.is_terminal());
+    }
+
+    #[test]
+    fn test_event_serialization() {
+        let event = JobEvent::state_changed("job-1", JobState::Queued, JobState::Assigned);
+        let json = serde_json::to_value(&event).unwrap();
+        assert_eq!(json["type"], "state_changed");
+        assert_eq!(json["to"], "assigned");
+        assert!(!event.is_terminal());
+    }
+}
@@ -1,16 +1,41 @@
 //! Job executor - orchestrates job execution using handlers
 
-use guestkit_job_spec::{JobDocument, JobValidator};
+use guestkit::core::AuditLog;
+use guestkit_job_spec::{JobDocument, JobValidator, SignaturePolicy};
 use chrono::Utc;
-use std::sync::Arc;
+use std::sync::{Arc, Mutex};
 use std::time::Duration;
+use guestkit_job_spec::{ExecutionMetrics, JobStatus};
+use crate::artifacts::ArtifactStore;
+use crate::audit;
+use crate::cancel::{CancelRegistry, CancelToken, ControlOutcome};
+use crate::capabilities::Capabilities;
+use crate::concurrency::{ConcurrencyLimiter, DEFAULT_TENANT};
 use crate::error::{WorkerError, WorkerResult};
+use crate::events::{EventBus, JobEvent};
 use crate::handler::{HandlerRegistry, HandlerContext};
+use crate::lease::LeaseStore;
 use crate::progress::ProgressTracker;
 use crate::result::ResultWriter;
 use crate::state::{JobState, JobStateMachine};
+use crate::store::JobStore;
 use crate::metrics::MetricsRegistry;
-use dashmap::DashMap;
+use crate::telemetry::TraceContext;
+use dashmap::{DashMap, DashSet};
+
+/// How long a cancelled handler may keep running before it is dropped
+const CANCEL_GRACE_PERIOD: Duration = Duration::from_secs(10);
+
+/// Whether a job's dependencies allow it to run
+#[derive(Debug, Clone, PartialEq)]
+pub enum DependencyStatus {
+    /// All dependencies completed successfully
+    Ready,
+    /// Dependencies without a result yet
+    Waiting(Vec<String>),
+    /// A dependency finished without succeeding
+    Failed { job_id: String, status: JobStatus },
+}
 
 /// Job executor
 pub struct JobExecutor {
@@ -31,6 +56,36 @@ pub struct JobExecutor {
 
     /// Metrics registry
     metrics: Option<Arc<MetricsRegistry>>,
+
+    /// Event bus for streaming progress and state changes
+    event_bus: Option<EventBus>,
+
+    /// Cancel tokens for queued and running jobs
+    cancellations: CancelRegistry,
+
+    /// Remote image fetch and result upload
+    artifacts: Option<Arc<ArtifactStore>>,
+
+    /// Persistent record of unfinished jobs
+    store: Option<Arc<JobStore>>,
+
+    /// Job claims shared with other workers
+    leases: Option<Arc<LeaseStore>>,
+
+    /// Trust anchors job signatures are verified against
+    signatures: Option<Arc<SignaturePolicy>>,
+
+    /// Schedule runs whose template signature was already verified
+    scheduled_runs: Arc<DashSet<String>>,
+
+    /// Worker resources and features job constraints are checked against
+    capabilities: Option<Arc<Capabilities>>,
+
+    /// Execution slots shared by all jobs of the worker
+    limiter: Option<Arc<ConcurrencyLimiter>>,
+
+    /// Hash-chained record of every job run
+    audit_log: Option<Arc<AuditLog>>,
 }
 
 impl JobExecutor {
@@ -48,6 +103,16 @@ impl JobExecutor {
             work_dir: work_dir.into(),
             idempotency_cache: Arc::new(DashMap::new()),
             metrics: None,
+            event_bus: None,
+            cancellations: CancelRegistry::new(),
+            artifacts: None,
+            store: None,
+            leases: None,
+            signatures: None,
+            scheduled_runs: Arc::new(DashSet::new()),
+            capabilities: None,
+            limiter: None,
+            audit_log: None,
         }
     }
 
@@ -57,8 +122,220 @@ impl JobExecutor {
         self
     }
 
+    /// Set event bus
+    pub fn with_event_bus(mut self, event_bus: EventBus) -> Self {
+        self.event_bus = Some(event_bus);
+        self
+    }
+
+    /// Set cancel registry (shared with the API)
+    pub fn with_cancel_registry(mut self, cancellations: CancelRegistry) -> Self {
+        self.cancellations = cancellations;
+        self
+    }
+
+    /// Set artifact store for remote images and result upload
+    pub fn with_artifact_store(mut self, artifacts: Arc<ArtifactStore>) -> Self {
+        self.artifacts = Some(artifacts);
+        self
+    }
+
+    /// Set job store so in-flight jobs survive a restart
+    pub fn with_job_store(mut self, store: Arc<JobStore>) -> Self {
+        self.store = Some(store);
+        self
+    }
+
+    /// Claim jobs before running them so workers sharing a job source do
+    /// not run the same job twice
+    pub fn with_lease_store(mut self, leases: Arc<LeaseStore>) -> Self {
+        self.leases = Some(leases);
+        self
+    }
+
+    /// Verify job signatures before running jobs
+    pub fn with_signature_policy(mut self, policy: Arc<SignaturePolicy>) -> Self {
+        self.signatures = Some(policy);
+        self
+    }
+
+    /// Reject jobs whose constraints the worker does not meet
+    pub fn with_capabilities(mut self, capabilities: Arc<Capabilities>) -> Self {
+        self.capabilities = Some(capabilities);
+        self
+    }
+
+    /// Wait for an execution slot before running each job
+    pub fn with_concurrency_limiter(mut self, limiter: Arc<ConcurrencyLimiter>) -> Self {
+        self.limiter = Some(limiter);
+        self
+    }
+
+    /// Record every job in an audit log
+    pub fn with_audit_log(mut self, audit_log: Arc<AuditLog>) -> Self {
+        self.audit_log = Some(audit_log);
+        self
+    }
+
+    /// Check a job's signature against the configured policy
+    pub fn verify_signature(&self, job: &JobDocument) -> WorkerResult<()> {
+        if let Some(ref policy) = self.signatures {
+            JobValidator::verify_signature(job, policy)?;
+        }
+        Ok(())
+    }
+
+    /// Accept a run instantiated from a verified schedule template
+    ///
+    /// Runs get a new job ID and creation time, so the template's signature
+    /// no longer matches them.
+    pub fn trust_scheduled_run(&self, job_id: &str) {
+        self.scheduled_runs.insert(job_id.to_string());
+    }
+
+    /// Request cancellation of a queued or running job
+    pub fn cancel(&self, job_id: &str) -> bool {
+        self.cancellations.cancel(job_id) == ControlOutcome::Applied
+    }
+
+    /// Pause a running job at its next checkpoint
+    pub fn pause(&self, job_id: &str) -> bool {
+        self.cancellations.pause(job_id) == ControlOutcome::Applied
+    }
+
+    /// Resume a paused job
+    pub fn resume(&self, job_id: &str) -> bool {
+        self.cancellations.resume(job_id) == ControlOutcome::Applied
+    }
+
+    /// Check dependencies against results written so far
+    pub async fn dependency_status(&self, job: &JobDocument) -> DependencyStatus {
+        let mut waiting = Vec::new();
+
+        for dep in &job.depends_on {
+            match self.result_writer.read_result(dep).await {
+                Ok(result) if result.status == JobStatus::Completed => {}
+                Ok(result) => {
+                    return DependencyStatus::Failed {
+                        job_id: dep.clone(),
+                        status: result.status,
+                    };
+                }
+                Err(_) => waiting.push(dep.clone()),
+            }
+        }
+
+        if waiting.is_empty() {
+            DependencyStatus::Ready
+        } else {
+            DependencyStatus::Waiting(waiting)
+        }
+    }
+
+    /// Transition job state and publish the change
+    fn transition(
+        &self,
+        job_id: &str,
+        state: &mut JobStateMachine,
+        target: JobState,
+    ) -> WorkerResult<()> {
+        let from = state.current();
+        state.transition(target)?;
+
+        if let Some(ref store) = self.store {
+            if let Err(e) = store.set_state(job_id, target) {
+                log::warn!("Failed to persist state of job {}: {}", job_id, e);
+            }
+        }
+
+        if let Some(ref bus) = self.event_bus {
+            bus.publish(JobEvent::state_changed(job_id, from, target));
+        }
+
+        Ok(())
+    }
+
     /// Execute a job
-    pub async fn execute(&self, job: JobDocument) -> WorkerResult<()> {
+    pub async fn execute(&self, mut job: JobDocument) -> WorkerResult<()> {
+        let job_id = job.job_id.clone();
+        let cancel = self.cancellations.register(&job_id);
+
+        if let Some(ref leases) = self.leases {
+            match leases.claim(&job_id).await {
+                Ok(Some(lease)) => {
+                    log::info!("Claimed job {} (fencing token {})", job_id, lease.token);
+                    job.metadata.get_or_insert_with(Default::default).fencing_token = Some(lease.token);
+                }
+                Ok(None) => {
+                    log::info!("Job {} is claimed by another worker, skipping", job_id);
+                    if let Some(ref store) = self.store {
+                        let _ = store.remove(&job_id);
+                    }
+                    self.cancellations.remove(&job_id);
+                    return Ok(());
+                }
+                Err(e) => {
+                    self.cancellations.remove(&job_id);
+                    return Err(e);
+                }
+            }
+        }
+
+        // Root span of the job on this worker
+        let span = TraceContext::from_job(&job).span("job");
+        span.set_attribute("job.id", job_id.clone());
+        span.set_attribute("job.operation", job.operation.clone());
+        span.set_attribute("worker.id", self.worker_id.clone());
+        if let Some(correlation_id) = job.observability.as_ref().and_then(|o| o.correlation_id.clone()) {
+            span.set_attribute("job.correlation_id", correlation_id);
+        }
+
+        if let Some(ref store) = self.store {
+            if let Err(e) = store.insert(&job, JobState::Pending, &self.worker_id) {
+                log::warn!("Failed to persist job {}: {}", job_id, e);
+            }
+        }
+
+        let audit_record = self
+            .audit_log
+            .as_ref()
+            .map(|_| audit::job_record(&job, &self.worker_id));
+
+        let outcome = self.execute_job(job, cancel, span.context()).await;
+        if let Err(ref e) = outcome {
+            span.record_error(e);
+        }
+
+        if let (Some(audit_log), Some(record)) = (&self.audit_log, audit_record) {
+            if let Err(e) = audit_log.append(record, audit::job_result(&outcome)) {
+                log::warn!("Failed to append job {} to audit log: {}", job_id, e);
+            }
+        }
+
+        // The result document is written by now (or the job was a duplicate)
+        if let Some(ref store) = self.store {
+            if let Err(e) = store.remove(&job_id) {
+                log::warn!("Failed to remove job {} from store: {}", job_id, e);
+            }
+        }
+
+        // A lost lease belongs to the worker that took the job over
+        if let Some(ref leases) = self.leases {
+            let completed = !matches!(outcome, Err(WorkerError::LeaseLost { .. }));
+            leases.release(&job_id, completed).await;
+        }
+
+        self.cancellations.remove(&job_id);
+        outcome
+    }
+
+    /// Run a job through validation and execution
+    async fn execute_job(
+        &self,
+        job: JobDocument,
+        cancel: CancelToken,
+        trace: TraceContext,
+    ) -> WorkerResult<()> {
         let job_id = job.job_id.clone();
         let operation = job.operation.clone();
         let started_at = Utc::now();
@@ -89,27 +366,123 @@ impl JobExecutor {
         }
 
         // Validate job
-        state.transition(JobState::Queued)?;
-        if let Err(e) = self.validate_job(&job).await {
+        self.transition(&job_id, &mut state, JobState::Queued)?;
+
+        // Cancelled while still queued
+        if cancel.is_cancelled() {
+            return self.cancel_queued(&job, &mut state, started_at, &trace).await;
+        }
+
+        let validation = {
+            let span = trace.span("job.validate");
+            let validation = self.validate_job(&job).await;
+            if let Err(ref e) = validation {
+                span.record_error(e);
+            }
+            validation
+        };
+
+        if let Err(e) = validation {
             log::error!("Job {} validation failed: {}", job_id, e);
+            self.transition(&job_id, &mut state, JobState::Failed)?;
+            let _report = trace.span("job.report");
+            match e {
+                WorkerError::ConstraintNotMet(ref unmet) => {
+                    self.result_writer
+                        .write_rejection(&job_id, &self.worker_id, started_at, 1, unmet)
+                        .await?;
+                }
+                _ => {
+                    self.result_writer
+                        .write_failure(
+                            &job_id,
+                            &self.worker_id,
+                            started_at,
+                            1,
+                            "VALIDATION_ERROR",
+                            e.to_string(),
+                            Some("validation".to_string()),
+                            false,
+                            None,
+                        )
+                        .await?;
+                }
+            }
+            return Err(e);
+        }
+
+        // Gate on dependencies
+        let reason = match self.dependency_status(&job).await {
+            DependencyStatus::Ready => None,
+            DependencyStatus::Waiting(deps) => Some((
+                "DEPENDENCY_PENDING",
+                format!("Dependencies not yet completed: {}", deps.join(", ")),
+                true,
+            )),
+            DependencyStatus::Failed { job_id: dep, status } => Some((
+                "DEPENDENCY_FAILED",
+                format!("Dependency {} finished with status {:?}", dep, status),
+                false,
+            )),
+        };
+
+        if let Some((code, message, recoverable)) = reason {
+            log::error!("Job {} blocked: {}", job_id, message);
+            self.transition(&job_id, &mut state, JobState::Failed)?;
+
+            if let Some(ref metrics) = self.metrics {
+                metrics.record_job_completion(&operation, "failed", 0.0);
+                metrics.dec_active_jobs();
+            }
+
+            let _report = trace.span("job.report");
             self.result_writer
                 .write_failure(
                     &job_id,
                     &self.worker_id,
                     started_at,
-                    1,
-                    "VALIDATION_ERROR",
-                    e.to_string(),
-                    Some("validation".to_string()),
-                    false,
+                    job.execution.as_ref().map(|e| e.attempt).unwrap_or(1),
+                    code,
+                    message.clone(),
+                    Some("dependencies".to_string()),
+                    recoverable,
+                    None,
                 )
                 .await?;
-            return Err(e);
+
+            return Err(WorkerError::DependencyNotMet(message));
+        }
+
+        // Wait for an execution slot
+        let tenant = job
+            .metadata
+            .as_ref()
+            .and_then(|m| m.namespace.clone())
+            .unwrap_or_else(|| DEFAULT_TENANT.to_string());
+        let _permit = match self.limiter {
+            Some(ref limiter) => {
+                let _queue = trace.span("job.queue");
+                tokio::select! {
+                    permit = limiter.acquire(&tenant, &operation) => Some(permit),
+                    _ = cancel.cancelled() => None,
+                }
+            }
+            None => None,
+        };
+        if cancel.is_cancelled() {
+            return self.cancel_queued(&job, &mut state, started_at, &trace).await;
         }
 
+        let run_started = Utc::now();
+        let queue_wait = (run_started - started_at).num_milliseconds() as f64 / 1000.0;
+        if let Some(ref metrics) = self.metrics {
+            metrics.record_queue_wait(&operation, &tenant, queue_wait);
+        }
+        log::debug!("Job {} waited {:.1}s for a slot", job_id, queue_wait);
+
         // Assign and run
-        state.transition(JobState::Assigned)?;
-        state.transition(JobState::Running)?;
+        self.transition(&job_id, &mut state, JobState::Assigned)?;
+        self.transition(&job_id, &mut state, JobState::Running)?;
 
         // Setup timeout
         let timeout = job.execution.as_ref()
@@ -117,20 +490,60 @@ impl JobExecutor {
             .unwrap_or(Duration::from_secs(3600));
 
         // Execute with timeout
-        let result = tokio::time::timeout(
+        let usage = Arc::new(Mutex::new(ExecutionMetrics::default()));
+
+        // Handler usage plus the split between queueing and running
+        let execution_metrics = || {
+            let mut recorded = usage.lock().unwrap().clone();
+            recorded.queue_wait_seconds = Some(queue_wait);
+            recorded.run_seconds =
+                Some((Utc::now() - run_started).num_milliseconds() as f64 / 1000.0);
+            recorded
+        };
+
+        let execute_span = trace.span("job.execute");
+        let handler_run = tokio::time::timeout(
             timeout,
-            self.execute_with_handler(job.clone())
-        ).await;
+            self.execute_with_handler(
+                job.clone(),
+                cancel.clone(),
+                Arc::clone(&usage),
+                execute_span.context(),
+            )
+        );
+        tokio::pin!(handler_run);
+
+        // Track pause and resume requests while the handler runs; the
+        // handler itself waits at its next checkpoint
+        let result = loop {
+            let paused = state.current() == JobState::Paused;
+            tokio::select! {
+                result = &mut handler_run => break result,
+                now_paused = cancel.pause_changed(paused) => {
+                    let target = if now_paused { JobState::Paused } else { JobState::Running };
+                    log::info!("Job {} {}", job_id, target);
+                    self.transition(&job_id, &mut state, target)?;
+                }
+            }
+        };
+        match result {
+            Ok(Err(ref e)) => execute_span.record_error(e),
+            Err(_) => execute_span.record_error(&"timeout"),
+            Ok(Ok(_)) => {}
+        }
+        drop(execute_span);
+
+        let _report = trace.span("job.report");
 
         match result {
             Ok(Ok(handler_result)) => {
                 // Success
-                state.transition(JobState::Completed)?;
+                self.transition(&job_id, &mut state, JobState::Completed)?;
 
                 log::info!("Job {} completed successfully", job_id);
 
                 // Record metrics
-                let duration = (Utc::now() - started_at).num_milliseconds() as f64 / 1000.0;
+                let duration = (Utc::now() - run_started).num_milliseconds() as f64 / 1000.0;
                 if let Some(ref metrics) = self.metrics {
                     metrics.record_job_completion(&operation, "completed", duration);
                     metrics.dec_active_jobs();
@@ -145,6 +558,7 @@ impl JobExecutor {
                         job.execution.as_ref().and_then(|e| e.idempotency_key.clone()),
                         handler_result.output_file,
                         handler_result.artifacts,
+                        Some(execution_metrics()),
                     )
                     .await?;
 
@@ -157,14 +571,43 @@ impl JobExecutor {
 
                 Ok(())
             }
+            Ok(Err(e)) if cancel.is_cancelled() => {
+                // Cancelled while running
+                self.transition(&job_id, &mut state, JobState::Cancelled)?;
+
+                log::warn!("Job {} cancelled: {}", job_id, e);
+
+                // Record metrics
+                let duration = (Utc::now() - run_started).num_milliseconds() as f64 / 1000.0;
+                if let Some(ref metrics) = self.metrics {
+                    metrics.record_job_completion(&operation, "cancelled", duration);
+                    metrics.dec_active_jobs();
+                }
+
+                // Keep whatever usage the handler recorded before stopping
+                let partial_metrics = execution_metrics();
+
+                self.result_writer
+                    .write_cancelled(
+                        &job_id,
+                        &self.worker_id,
+                        started_at,
+                        job.execution.as_ref().map(|e| e.attempt).unwrap_or(1),
+                        "Job cancelled by request",
+                        Some(partial_metrics),
+                    )
+                    .await?;
+
+                Err(WorkerError::Cancelled(job_id))
+            }
             Ok(Err(e)) => {
                 // Execution error
-                state.transition(JobState::Failed)?;
+                self.transition(&job_id, &mut state, JobState::Failed)?;
 
                 log::error!("Job {} failed: {}", job_id, e);
 
                 // Record metrics
-                let duration = (Utc::now() - started_at).num_milliseconds() as f64 / 1000.0;
+                let duration = (Utc::now() - run_started).num_milliseconds() as f64 / 1000.0;
                 if let Some(ref metrics) = self.metrics {
                     metrics.record_job_completion(&operation, "failed", duration);
                     metrics.dec_active_jobs();
@@ -180,6 +623,7 @@ impl JobExecutor {
                         e.to_string(),
                         Some("execution".to_string()),
                         true,
+                        Some(execution_metrics()),
                     )
                     .await?;
 
@@ -187,7 +631,7 @@ impl JobExecutor {
             }
             Err(_) => {
                 // Timeout
-                state.transition(JobState::Timeout)?;
+                self.transition(&job_id, &mut state, JobState::Timeout)?;
 
                 log::error!("Job {} timed out after {:?}", job_id, timeout);
 
@@ -208,6 +652,7 @@ impl JobExecutor {
                         format!("Job exceeded timeout of {:?}", timeout),
                         Some("execution".to_string()),
                         true,
+                        Some(execution_metrics()),
                     )
                     .await?;
 
@@ -218,16 +663,59 @@ impl JobExecutor {
         }
     }
 
+    /// Record a job cancelled before it started running
+    async fn cancel_queued(
+        &self,
+        job: &JobDocument,
+        state: &mut JobStateMachine,
+        started_at: chrono::DateTime<Utc>,
+        trace: &TraceContext,
+    ) -> WorkerResult<()> {
+        log::info!("Job {} cancelled before execution", job.job_id);
+        self.transition(&job.job_id, state, JobState::Cancelled)?;
+
+        if let Some(ref metrics) = self.metrics {
+            metrics.record_job_completion(&job.operation, "cancelled", 0.0);
+            metrics.dec_active_jobs();
+        }
+
+        let _report = trace.span("job.report");
+        self.result_writer
+            .write_cancelled(
+                &job.job_id,
+                &self.worker_id,
+                started_at,
+                job.execution.as_ref().map(|e| e.attempt).unwrap_or(1),
+                "Job cancelled before execution",
+                None,
+            )
+            .await?;
+
+        Err(WorkerError::Cancelled(job.job_id.clone()))
+    }
+
     /// Validate job before execution
     async fn validate_job(&self, job: &JobDocument) -> WorkerResult<()> {
         // Validate protocol
         JobValidator::validate(job)?;
 
+        // Verify signature
+        if self.scheduled_runs.remove(&job.job_id).is_none() {
+            self.verify_signature(job)?;
+        }
+
         // Check if operation is supported
         if !self.registry.supports(&job.operation) {
             return Err(WorkerError::HandlerNotFound(job.operation.clone()));
         }
 
+        // Check resource and feature constraints
+        if let (Some(capabilities), Some(constraints)) = (&self.capabilities, &job.constraints) {
+            capabilities
+                .check_constraints(constraints)
+                .map_err(WorkerError::ConstraintNotMet)?;
+        }
+
         // Get handler and validate payload
         if let Some(handler) = self.registry.get(&job.operation) {
             handler.validate(&job.payload).await?;
@@ -240,6 +728,9 @@ impl JobExecutor {
     async fn execute_with_handler(
         &self,
         job: JobDocument,
+        cancel: CancelToken,
+        usage: Arc<Mutex<ExecutionMetrics>>,
+        trace: TraceContext,
     ) -> WorkerResult<crate::handler::HandlerResult> {
         let handler = self.registry
             .get(&job.operation)
@@ -248,9 +739,10 @@ impl JobExecutor {
         // Create progress tracker
         let (progress, mut rx) = ProgressTracker::new(&job.job_id);
 
-        // Spawn progress logger
+        // Spawn progress logger (and forward to event bus subscribers)
         let job_id = job.job_id.clone();
-        tokio::spawn(async move {
+        let event_bus = self.event_bus.clone();
+        let progress_logger = tokio::spawn(async move {
             while let Some(event) = rx.recv().await {
                 log::info!(
                     "[{}] {} - {} ({}%)",
@@ -259,6 +751,9 @@ impl JobExecutor {
                     event.message,
                     event.progress_percent.unwrap_or(0)
                 );
+                if let Some(ref bus) = event_bus {
+                    bus.publish(JobEvent::Progress(Box::new(event)));
+                }
             }
         });
 
@@ -268,32 +763,80 @@ impl JobExecutor {
             self.worker_id.clone(),
             Arc::new(progress),
             self.work_dir.clone(),
-        );
+        )
+        .with_cancel_token(cancel.clone())
+        .with_trace(trace);
+        context.usage = usage;
 
         // Attach metrics if available
         if let Some(ref metrics) = self.metrics {
             context = context.with_metrics(Arc::clone(metrics));
         }
 
+        if let Some(ref artifacts) = self.artifacts {
+            context = context.with_artifacts(Arc::clone(artifacts));
+        }
+
+        if let Some(ref constraints) = job.constraints {
+            context = context.with_constraints(constraints.clone());
+        }
+
         // Execute handler with metrics
         let handler_name = handler.name();
         let handler_start = std::time::Instant::now();
-        let result = handler.execute(context.clone(), job.payload).await;
+        let result = tokio::select! {
+            result = handler.execute(context.clone(), job.payload) => result,
+            _ = Self::cancel_deadline(&cancel) => {
+                log::warn!(
+                    "Handler for job {} ignored cancellation for {:?}, aborting",
+                    job.job_id,
+                    CANCEL_GRACE_PERIOD
+                );
+                Err(WorkerError::Cancelled(job.job_id.clone()))
+            }
+        };
         let handler_duration = handler_start.elapsed().as_secs_f64();
 
         // Record handler metrics
         if let Some(ref metrics) = self.metrics {
             let status = if result.is_ok() { "success" } else { "error" };
             metrics.record_handler_execution(handler_name, status, handler_duration);
+            if let Err(ref e) = result {
+                metrics.record_handler_error(handler_name, e.kind());
+            }
         }
+        context.clear_bytes_processed(&job.operation);
 
         // Cleanup (always run, even on failure)
         if let Err(e) = handler.cleanup(&context).await {
             log::warn!("Cleanup failed for job {}: {}", job.job_id, e);
         }
 
+        // Upload outputs before the result document points at them
+        let result = match (&self.artifacts, result) {
+            (Some(artifacts), Ok(handler_result)) if artifacts.uploads_enabled() => {
+                let _span = context.span("job.upload");
+                let _ = context.report_progress("upload", None, "Uploading job outputs").await;
+                artifacts.upload_result(&job.job_id, handler_result).await
+            }
+            (_, result) => result,
+        };
+        if let Some(ref artifacts) = self.artifacts {
+            artifacts.cleanup(&job.job_id).await;
+        }
+
+        // Let queued progress drain before the final state change is published
+        drop(context);
+        let _ = tokio::time::timeout(Duration::from_secs(1), progress_logger).await;
+
         result
     }
+
+    /// Resolve once a cancelled handler has used up its grace period
+    async fn cancel_deadline(cancel: &CancelToken) {
+        cancel.cancelled().await;
+        tokio::time::sleep(CANCEL_GRACE_PERIOD).await;
+    }
 }
 
 #[cfg(test)]
@@ -352,4 +895,487 @@ mod tests {
         let result = executor.execute(job).await;
         assert!(result.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_executor_writes_audit_log() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let mut registry = HandlerRegistry::new();
+        registry.register(Arc::new(TestHandler));
+
+        let audit_log = Arc::new(AuditLog::new(temp_dir.path().join("audit.jsonl")));
+        let executor = JobExecutor::new(
+            "worker-test",
+            Arc::new(registry),
+            Arc::new(ResultWriter::new(temp_dir.path())),
+            temp_dir.path(),
+        )
+        .with_audit_log(Arc::clone(&audit_log));
+
+        for job_id in ["audit-job-1", "audit-job-2"] {
+            let job = JobBuilder::new()
+                .job_id(job_id)
+                .operation("test.operation")
+                .payload("test.operation.v1", serde_json::json!({}))
+                .build()
+                .unwrap();
+            executor.execute(job).await.unwrap();
+        }
+
+        let entries = audit_log.entries().unwrap();
+        assert_eq!(entries.len(), 2);
+        assert_eq!(entries[1].operation, "test.operation");
+        assert_eq!(entries[1].parameters["job_id"], "audit-job-2");
+        assert!(audit_log.verify().unwrap().is_valid());
+    }
+
+    #[tokio::test]
+    async fn test_executor_waits_for_slot() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let mut registry = HandlerRegistry::new();
+        registry.register(Arc::new(TestHandler));
+
+        let limiter = Arc::new(ConcurrencyLimiter::new(1, Vec::new()));
+        let result_writer = Arc::new(ResultWriter::new(temp_dir.path()));
+        let executor = Arc::new(
+            JobExecutor::new("worker-test", Arc::new(registry), Arc::clone(&result_writer), temp_dir.path())
+                .with_concurrency_limiter(Arc::clone(&limiter)),
+        );
+
+        let job = |job_id: &str| {
+            JobBuilder::new()
+                .job_id(job_id)
+                .operation("test.operation")
+                .payload("test.operation.v1", serde_json::json!({}))
+                .build()
+                .unwrap()
+        };
+
+        // Queued behind another job, then cancelled before it gets a slot
+        let held = limiter.acquire("other", "test.operation").await;
+        let queued = {
+            let executor = Arc::clone(&executor);
+            let job = job("test-job-queued");
+            tokio::spawn(async move { executor.execute(job).await })
+        };
+        tokio::time::sleep(Duration::from_millis(50)).await;
+        assert_eq!(limiter.waiting(), 1);
+        assert!(executor.cancel("test-job-queued"));
+        assert!(matches!(queued.await.unwrap(), Err(WorkerError::Cancelled(_))));
+        let result = result_writer.read_result("test-job-queued").await.unwrap();
+        assert_eq!(result.status, JobStatus::Cancelled);
+
+        // Runs once the slot is free, recording wait and run time
+        drop(held);
+        executor.execute(job("test-job-run")).await.unwrap();
+        let metrics = result_writer.read_result("test-job-run").await.unwrap().metrics.unwrap();
+        assert!(metrics.queue_wait_seconds.is_some());
+        assert!(metrics.run_seconds.is_some());
+        assert_eq!(limiter.running(), 0);
+    }
+
+    #[tokio::test]
+    async fn test_executor_clears_job_store() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let mut registry = HandlerRegistry::new();
+        registry.register(Arc::new(TestHandler));
+
+        let store = Arc::new(JobStore::temporary().unwrap());
+        let executor = JobExecutor::new(
+            "worker-test",
+            Arc::new(registry),
+            Arc::new(ResultWriter::new(temp_dir.path())),
+            temp_dir.path(),
+        )
+        .with_job_store(Arc::clone(&store));
+
+        let job = JobBuilder::new()
+            .job_id("test-job-store")
+            .operation("test.operation")
+            .payload("test.operation.v1", serde_json::json!({}))
+            .build()
+            .unwrap();
+
+        executor.execute(job).await.unwrap();
+        assert!(store.is_empty());
+    }
+
+    #[tokio::test]
+    async fn test_executors_share_job_claims() {
+        let temp_dir = TempDir::new().unwrap();
+        let lease_dir = temp_dir.path().join("leases");
+
+        let executor = |worker_id: &str| {
+            let mut registry = HandlerRegistry::new();
+            registry.register(Arc::new(TestHandler));
+            let leases = Arc::new(LeaseStore::new(&lease_dir, worker_id, 30).unwrap());
+            let result_writer = Arc::new(
+                ResultWriter::new(temp_dir.path().join(worker_id)).with_leases(Arc::clone(&leases)),
+            );
+            JobExecutor::new(worker_id, Arc::new(registry), result_writer, temp_dir.path())
+                .with_lease_store(leases)
+        };
+        let a = executor("worker-a");
+        let b = executor("worker-b");
+
+        let job = JobBuilder::new()
+            .job_id("test-job-claim")
+            .operation("test.operation")
+            .payload("test.operation.v1", serde_json::json!({}))
+            .build()
+            .unwrap();
+
+        a.execute(job.clone()).await.unwrap();
+        b.execute(job).await.unwrap();
+
+        // Only the worker that claimed the job wrote a result
+        assert!(ResultWriter::new(temp_dir.path().join("worker-a")).result_exists("test-job-claim").await);
+        assert!(!ResultWriter::new(temp_dir.path().join("worker-b")).result_exists("test-job-claim").await);
+    }
+
+    #[tokio::test]
+    async fn test_executor_verifies_signatures() {
+        use guestkit_job_spec::signature::generate_ed25519_pkcs8;
+        use guestkit_job_spec::{JobSigner, TrustAnchors};
+
+        let temp_dir = TempDir::new().unwrap();
+
+        let mut registry = HandlerRegistry::new();
+        registry.register(Arc::new(TestHandler));
+
+        let signer = JobSigner::from_pkcs8_der("ops", &generate_ed25519_pkcs8().unwrap()).unwrap();
+        let mut anchors = TrustAnchors::new();
+        anchors.insert("ops", signer.public_key());
+
+        let result_writer = Arc::new(ResultWriter::new(temp_dir.path()));
+        let executor = JobExecutor::new(
+            "worker-test",
+            Arc::new(registry),
+            Arc::clone(&result_writer),
+            temp_dir.path(),
+        )
+        .with_signature_policy(Arc::new(SignaturePolicy::new(anchors).strict()));
+
+        let job = |job_id: &str| {
+            JobBuilder::new()
+                .job_id(job_id)
+                .operation("test.operation")
+                .payload("test.operation.v1", serde_json::json!({}))
+                .build()
+                .unwrap()
+        };
+
+        let mut signed = job("test-job-signed");
+        signer.sign(&mut signed).unwrap();
+        executor.execute(signed).await.unwrap();
+
+        let mut tampered = job("test-job-tampered");
+        signer.sign(&mut tampered).unwrap();
+        tampered.payload.data = serde_json::json!({"image": "/vms/other.qcow2"});
+        assert!(executor.execute(tampered).await.is_err());
+
+        assert!(executor.execute(job("test-job-unsigned")).await.is_err());
+
+        // Schedule runs were verified as templates
+        executor.trust_scheduled_run("test-job-scheduled-run");
+        executor.execute(job("test-job-scheduled-run")).await.unwrap();
+
+        let result = result_writer.read_result("test-job-unsigned").await.unwrap();
+        assert_eq!(result.status, JobStatus::Failed);
+    }
+
+    #[tokio::test]
+    async fn test_executor_enforces_constraints() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let mut registry = HandlerRegistry::new();
+        registry.register(Arc::new(TestHandler));
+
+        let result_writer = Arc::new(ResultWriter::new(temp_dir.path()));
+        let executor = JobExecutor::new(
+            "worker-test",
+            Arc::new(registry),
+            Arc::clone(&result_writer),
+            temp_dir.path(),
+        )
+        .with_capabilities(Arc::new(
+            Capabilities::new().with_max_disk_size_gb(100).with_resources(4, 8),
+        ));
+
+        let job = |job_id: &str, disk_size_gb: u64| {
+            JobBuilder::new()
+                .job_id(job_id)
+                .operation("test.operation")
+                .payload("test.operation.v1", serde_json::json!({}))
+                .max_disk_size_gb(disk_size_gb)
+                .build()
+                .unwrap()
+        };
+
+        executor.execute(job("test-job-fits", 100)).await.unwrap();
+
+        let err = executor.execute(job("test-job-too-big", 500)).await.unwrap_err();
+        assert!(matches!(err, WorkerError::ConstraintNotMet(_)));
+
+        let error = result_writer
+            .read_result("test-job-too-big")
+            .await
+            .unwrap()
+            .error
+            .unwrap();
+        assert_eq!(error.code, "CONSTRAINT_NOT_MET");
+        let details = error.details.unwrap();
+        assert_eq!(details["constraint"], "constraints.maximum_disk_size_gb");
+        assert_eq!(details["required"], 500);
+        assert_eq!(details["available"], 100);
+    }
+
+    struct SlowHandler;
+
+    #[async_trait]
+    impl OperationHandler for SlowHandler {
+        fn name(&self) -> &str {
+            "slow-handler"
+        }
+
+        fn operations(&self) -> Vec<String> {
+            vec!["test.slow".to_string()]
+        }
+
+        async fn execute(
+            &self,
+            context: HandlerContext,
+            _payload: Payload,
+        ) -> WorkerResult<HandlerResult> {
+            for _ in 0..100 {
+                context.checkpoint().await?;
+                context.record_disk_read(512);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+            Ok(HandlerResult::new())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_executor_cancel_running_job() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let mut registry = HandlerRegistry::new();
+        registry.register(Arc::new(SlowHandler));
+
+        let result_writer = Arc::new(ResultWriter::new(temp_dir.path()));
+
+        let executor = JobExecutor::new(
+            "worker-test",
+            Arc::new(registry),
+            Arc::clone(&result_writer),
+            temp_dir.path(),
+        );
+
+        let job = JobBuilder::new()
+            .job_id("test-job-cancel")
+            .operation("test.slow")
+            .payload("test.slow.v1", serde_json::json!({}))
+            .build()
+            .unwrap();
+
+        let (result, _) = tokio::join!(executor.execute(job), async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert!(executor.cancel("test-job-cancel"));
+        });
+
+        assert!(matches!(result, Err(WorkerError::Cancelled(_))));
+
+        let written = result_writer.read_result("test-job-cancel").await.unwrap();
+        assert_eq!(written.status, guestkit_job_spec::JobStatus::Cancelled);
+        assert!(written.metrics.unwrap().disk_read_bytes.unwrap() > 0);
+    }
+
+    #[tokio::test]
+    async fn test_executor_pause_resume_running_job() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let mut registry = HandlerRegistry::new();
+        registry.register(Arc::new(SlowHandler));
+
+        let result_writer = Arc::new(ResultWriter::new(temp_dir.path()));
+        let event_bus = EventBus::new();
+        let mut events = event_bus.subscribe();
+
+        let executor = JobExecutor::new(
+            "worker-test",
+            Arc::new(registry),
+            Arc::clone(&result_writer),
+            temp_dir.path(),
+        )
+        .with_event_bus(event_bus);
+
+        let job = JobBuilder::new()
+            .job_id("test-job-pause")
+            .operation("test.slow")
+            .payload("test.slow.v1", serde_json::json!({}))
+            .build()
+            .unwrap();
+
+        let (result, _) = tokio::join!(executor.execute(job), async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert!(executor.pause("test-job-pause"));
+            assert!(!executor.pause("test-job-pause"));
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            assert!(executor.resume("test-job-pause"));
+        });
+        assert!(result.is_ok());
+
+        let mut states = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if let JobEvent::StateChanged { to, .. } = event {
+                states.push(to);
+            }
+        }
+        assert!(states.ends_with(&[
+            JobState::Running,
+            JobState::Paused,
+            JobState::Running,
+            JobState::Completed,
+        ]));
+
+        // The token is gone once the job finished
+        assert!(!executor.pause("test-job-pause"));
+    }
+
+    #[tokio::test]
+    async fn test_executor_records_metrics() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let mut registry = HandlerRegistry::new();
+        registry.register(Arc::new(SlowHandler));
+
+        let metrics = Arc::new(MetricsRegistry::new());
+        let executor = JobExecutor::new(
+            "worker-test",
+            Arc::new(registry),
+            Arc::new(ResultWriter::new(temp_dir.path())),
+            temp_dir.path(),
+        )
+        .with_metrics(Arc::clone(&metrics));
+
+        let job = JobBuilder::new()
+            .job_id("test-job-metrics")
+            .operation("test.slow")
+            .payload("test.slow.v1", serde_json::json!({}))
+            .build()
+            .unwrap();
+
+        let _ = tokio::join!(executor.execute(job), async {
+            tokio::time::sleep(Duration::from_millis(50)).await;
+            executor.cancel("test-job-metrics");
+        });
+
+        let encoded = metrics.encode();
+        assert!(encoded.contains(r#"guestkit_handler_errors_total{handler="slow-handler",kind="cancelled"} 1"#));
+        assert!(encoded.contains(r#"guestkit_worker_jobs_total{operation="test.slow",status="cancelled"} 1"#));
+        assert!(!encoded.contains("guestkit_worker_disk_read_bytes_total 0"));
+    }
+
+    #[tokio::test]
+    async fn test_executor_dependency_gating() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let mut registry = HandlerRegistry::new();
+        registry.register(Arc::new(TestHandler));
+
+        let result_writer = Arc::new(ResultWriter::new(temp_dir.path()));
+
+        let executor = JobExecutor::new(
+            "worker-test",
+            Arc::new(registry),
+            Arc::clone(&result_writer),
+            temp_dir.path(),
+        );
+
+        let inspect = JobBuilder::new()
+            .job_id("test-job-inspect")
+            .operation("test.operation")
+            .payload("test.operation.v1", serde_json::json!({}))
+            .build()
+            .unwrap();
+
+        let convert = JobBuilder::new()
+            .job_id("test-job-convert")
+            .operation("test.operation")
+            .payload("test.operation.v1", serde_json::json!({}))
+            .depends_on("test-job-inspect")
+            .build()
+            .unwrap();
+
+        assert_eq!(
+            executor.dependency_status(&convert).await,
+            DependencyStatus::Waiting(vec!["test-job-inspect".to_string()])
+        );
+
+        executor.execute(inspect).await.unwrap();
+        assert_eq!(executor.dependency_status(&convert).await, DependencyStatus::Ready);
+        executor.execute(convert).await.unwrap();
+
+        let orphan = JobBuilder::new()
+            .job_id("test-job-orphan")
+            .operation("test.operation")
+            .payload("test.operation.v1", serde_json::json!({}))
+            .depends_on("test-job-missing")
+            .build()
+            .unwrap();
+
+        let result = executor.execute(orphan).await;
+        assert!(matches!(result, Err(WorkerError::DependencyNotMet(_))));
+
+        let written = result_writer.read_result("test-job-orphan").await.unwrap();
+        assert_eq!(written.error.unwrap().code, "DEPENDENCY_PENDING");
+    }
+
+    #[tokio::test]
+    async fn test_executor_publishes_events() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let mut registry = HandlerRegistry::new();
+        registry.register(Arc::new(TestHandler));
+
+        let result_writer = Arc::new(ResultWriter::new(temp_dir.path()));
+        let event_bus = EventBus::new();
+        let mut rx = event_bus.subscribe();
+
+        let executor = JobExecutor::new(
+            "worker-test",
+            Arc::new(registry),
+            result_writer,
+            temp_dir.path(),
+        )
+        .with_event_bus(event_bus);
+
+        let job = JobBuilder::new()
+            .job_id("test-job-events")
+            .operation("test.operation")
+            .payload("test.operation.v1", serde_json::json!({}))
+            .build()
+            .unwrap();
+
+        executor.execute(job).await.unwrap();
+
+        // Progress is forwarded asynchronously, so it may trail the final state
+        let mut saw_progress = false;
+        let mut saw_terminal = false;
+        while !(saw_progress && saw_terminal) {
+            let event = tokio::time::timeout(Duration::from_secs(5), rx.recv())
+                .await
+                .unwrap()
+                .unwrap();
+            assert_eq!(event.job_id(), "test-job-events");
+            if let JobEvent::Progress(ref progress) = event {
+                assert_eq!(progress.phase, "testing");
+                saw_progress = true;
+            }
+            saw_terminal |= event.is_terminal();
+        }
+    }
 }
@@ -1,12 +1,16 @@
 //! Operation handler trait and registry
 
 use async_trait::async_trait;
-use guestkit_job_spec::{JobDocument, Payload};
+use guestkit_job_spec::{Constraints, ExecutionMetrics, JobDocument, Payload};
 use std::collections::HashMap;
-use std::sync::Arc;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::{Arc, Mutex};
+use crate::artifacts::ArtifactStore;
+use crate::cancel::CancelToken;
 use crate::error::{WorkerError, WorkerResult};
 use crate::progress::ProgressTracker;
 use crate::metrics::MetricsRegistry;
+use crate::telemetry::{Span, TraceContext};
 
 /// Context provided to operation handlers
 #[derive(Debug, Clone)]
@@ -25,6 +29,24 @@ pub struct HandlerContext {
 
     /// Metrics registry (optional)
     pub metrics: Option<Arc<MetricsRegistry>>,
+
+    /// Cancellation signal for this job
+    pub cancel: CancelToken,
+
+    /// Resource usage recorded by the handler so far
+    pub usage: Arc<Mutex<ExecutionMetrics>>,
+
+    /// Remote image fetch (optional)
+    pub artifacts: Option<Arc<ArtifactStore>>,
+
+    /// Trace context of the handler's execute span
+    pub trace: TraceContext,
+
+    /// Constraints of the job, including its I/O limits
+    pub constraints: Constraints,
+
+    /// Bytes processed last reported, included in the metrics gauge
+    bytes_processed: Arc<AtomicU64>,
 }
 
 impl HandlerContext {
@@ -41,6 +63,12 @@ impl HandlerContext {
             progress,
             work_dir: work_dir.into(),
             metrics: None,
+            cancel: CancelToken::new(),
+            usage: Arc::new(Mutex::new(ExecutionMetrics::default())),
+            artifacts: None,
+            trace: TraceContext::default(),
+            constraints: Constraints::default(),
+            bytes_processed: Arc::new(AtomicU64::new(0)),
         }
     }
 
@@ -50,6 +78,108 @@ impl HandlerContext {
         self
     }
 
+    /// Attach artifact store for remote image references
+    pub fn with_artifacts(mut self, artifacts: Arc<ArtifactStore>) -> Self {
+        self.artifacts = Some(artifacts);
+        self
+    }
+
+    /// Attach cancellation token
+    pub fn with_cancel_token(mut self, cancel: CancelToken) -> Self {
+        self.cancel = cancel;
+        self
+    }
+
+    /// Attach trace context for handler spans
+    pub fn with_trace(mut self, trace: TraceContext) -> Self {
+        self.trace = trace;
+        self
+    }
+
+    /// Attach the job's constraints
+    pub fn with_constraints(mut self, constraints: Constraints) -> Self {
+        self.constraints = constraints;
+        self
+    }
+
+    /// Start a span under the job's execute span
+    ///
+    /// Blocking guestfs work runs on another thread; clone `trace` into the
+    /// closure and use [`TraceContext::in_span`] there instead.
+    pub fn span(&self, name: &'static str) -> Span {
+        self.trace.span(name)
+    }
+
+    /// Check if the job has been cancelled
+    pub fn is_cancelled(&self) -> bool {
+        self.cancel.is_cancelled()
+    }
+
+    /// Return an error if the job has been cancelled
+    ///
+    /// For blocking code that cannot wait at a [`checkpoint`](Self::checkpoint).
+    pub fn check_cancelled(&self) -> WorkerResult<()> {
+        if self.is_cancelled() {
+            return Err(WorkerError::Cancelled(self.job_id.clone()));
+        }
+        Ok(())
+    }
+
+    /// Wait while the job is paused, then return an error if it was cancelled
+    ///
+    /// Long-running handlers should call this between steps so they can be
+    /// paused and stopped at a safe point.
+    pub async fn checkpoint(&self) -> WorkerResult<()> {
+        if self.cancel.is_paused() {
+            log::info!("Job {} paused", self.job_id);
+            self.cancel.resumed().await;
+            log::info!("Job {} resumed", self.job_id);
+        }
+        self.check_cancelled()
+    }
+
+    /// Record bytes read from disk images
+    pub fn record_disk_read(&self, bytes: u64) {
+        let mut usage = self.usage.lock().unwrap();
+        usage.disk_read_bytes = Some(usage.disk_read_bytes.unwrap_or(0) + bytes);
+        if let Some(ref metrics) = self.metrics {
+            metrics.record_disk_io(bytes, 0);
+        }
+    }
+
+    /// Record bytes written to disk images
+    pub fn record_disk_write(&self, bytes: u64) {
+        let mut usage = self.usage.lock().unwrap();
+        usage.disk_write_bytes = Some(usage.disk_write_bytes.unwrap_or(0) + bytes);
+        if let Some(ref metrics) = self.metrics {
+            metrics.record_disk_io(0, bytes);
+        }
+    }
+
+    /// Report bytes processed so far by a long-running operation
+    ///
+    /// The metrics gauge sums running jobs per operation; per-job progress
+    /// goes out as progress events.
+    pub fn record_bytes_processed(&self, operation: &str, bytes: u64) {
+        let previous = self.bytes_processed.swap(bytes, Ordering::Relaxed);
+        if let Some(ref metrics) = self.metrics {
+            metrics.add_bytes_processed(operation, bytes as i64 - previous as i64);
+        }
+    }
+
+    /// Take the job's bytes processed back out of the metrics gauge
+    pub fn clear_bytes_processed(&self, operation: &str) {
+        let previous = self.bytes_processed.swap(0, Ordering::Relaxed);
+        if let Some(ref metrics) = self.metrics {
+            metrics.add_bytes_processed(operation, -(previous as i64));
+        }
+    }
+
+    /// Snapshot of the resource usage recorded so far
+    pub fn execution_metrics(&self) -> ExecutionMetrics {
+        self.usage.lock().unwrap().clone()
+    }
+
     /// Report progress
     pub async fn report_progress(
         &self,
@@ -255,4 +385,28 @@ mod tests {
         assert!(!registry.supports("guestkit.fix"));
         assert_eq!(registry.len(), 2);
     }
+
+    #[test]
+    fn test_bytes_processed_per_operation() {
+        let metrics = Arc::new(MetricsRegistry::new());
+        let context = |job_id: &str| {
+            let (progress, _rx) = ProgressTracker::new(job_id);
+            HandlerContext::new(job_id, "worker-1", Arc::new(progress), "/tmp")
+                .with_metrics(Arc::clone(&metrics))
+        };
+        let first = context("job-1");
+        let second = context("job-2");
+
+        first.record_bytes_processed("guestkit.convert", 1000);
+        first.record_bytes_processed("guestkit.convert", 3000);
+        second.record_bytes_processed("guestkit.convert", 500);
+        assert!(metrics
+            .encode()
+            .contains(r#"guestkit_worker_bytes_processed{operation="guestkit.convert"} 3500"#));
+
+        first.clear_bytes_processed("guestkit.convert");
+        assert!(metrics
+            .encode()
+            .contains(r#"guestkit_worker_bytes_processed{operation="guestkit.convert"} 500"#));
+    }
 }
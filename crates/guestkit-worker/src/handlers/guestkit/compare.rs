@@ -0,0 +1,702 @@
+//! Guestkit compare handler - Diff two VM disk images
+
+use async_trait::async_trait;
+use guestkit_job_spec::Payload;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, BTreeSet};
+use crate::error::{WorkerError, WorkerResult};
+use crate::handler::{OperationHandler, HandlerContext, HandlerResult};
+use crate::telemetry::TraceContext;
+
+/// Configuration files compared by checksum when `compare_config` is set
+const CONFIG_FILES: &[&str] = &[
+    "/etc/fstab",
+    "/etc/hosts",
+    "/etc/resolv.conf",
+    "/etc/ssh/sshd_config",
+    "/etc/sudoers",
+    "/etc/sysctl.conf",
+    "/etc/selinux/config",
+    "/etc/default/grub",
+    "/etc/crontab",
+];
+
+/// Directories walked when `compare_files` is set
+const FILE_ROOTS: &[&str] = &["/etc", "/usr/local", "/opt"];
+
+/// Compare operation payload
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct ComparePayload {
+    baseline: ImageSpec,
+    target: ImageSpec,
+    #[serde(default)]
+    options: CompareOptions,
+    #[serde(default)]
+    output: Option<OutputSpec>,
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct ImageSpec {
+    path: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    format: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    checksum: Option<String>,
+}
+
+fn default_true() -> bool {
+    true
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct CompareOptions {
+    #[serde(default = "default_true")]
+    compare_packages: bool,
+    /// Checksum every file under /etc, /usr/local and /opt
+    #[serde(default)]
+    compare_files: bool,
+    #[serde(default = "default_true")]
+    compare_config: bool,
+    /// Treat files with identical content but different mtimes as unchanged
+    #[serde(default = "default_true")]
+    ignore_timestamps: bool,
+}
+
+impl Default for CompareOptions {
+    fn default() -> Self {
+        Self {
+            compare_packages: true,
+            compare_files: false,
+            compare_config: true,
+            ignore_timestamps: true,
+        }
+    }
+}
+
+fn default_format() -> String {
+    "json".to_string()
+}
+
+#[derive(Debug, Clone, Deserialize, Serialize)]
+struct OutputSpec {
+    #[serde(default = "default_format")]
+    format: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    destination: Option<String>,
+    #[serde(default = "default_true")]
+    include_recommendations: bool,
+}
+
+impl Default for OutputSpec {
+    fn default() -> Self {
+        Self {
+            format: default_format(),
+            destination: None,
+            include_recommendations: true,
+        }
+    }
+}
+
+/// Content and modification time of a file in the image
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+struct FileStamp {
+    sha256: String,
+    mtime: i64,
+}
+
+/// State of one image relevant to the comparison
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ImageSnapshot {
+    os: BTreeMap<String, String>,
+    /// Package name to version
+    packages: BTreeMap<String, String>,
+    /// Regular (uid 1000-65533) user names
+    users: BTreeSet<String>,
+    services: BTreeSet<String>,
+    /// Interface name to its addresses
+    network: BTreeMap<String, String>,
+    config: BTreeMap<String, String>,
+    files: BTreeMap<String, FileStamp>,
+}
+
+/// Individual change record
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+struct Change {
+    field: String,
+    old_value: String,
+    new_value: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct PackageChanges {
+    added: Vec<String>,
+    removed: Vec<String>,
+    updated: Vec<PackageUpdate>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct PackageUpdate {
+    name: String,
+    old_version: String,
+    new_version: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct ServiceChanges {
+    enabled: Vec<String>,
+    disabled: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct UserChanges {
+    added: Vec<String>,
+    removed: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct FileChanges {
+    added: Vec<String>,
+    removed: Vec<String>,
+    modified: Vec<String>,
+}
+
+/// Diff between the baseline and target images
+///
+/// Uses the same sections as `guestctl diff`, with full package versions
+/// and an optional file section.
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+struct CompareDiff {
+    os_changes: Vec<Change>,
+    package_changes: PackageChanges,
+    service_changes: ServiceChanges,
+    user_changes: UserChanges,
+    network_changes: Vec<Change>,
+    config_changes: Vec<Change>,
+    file_changes: FileChanges,
+}
+
+/// Push a change for every key whose value differs between two maps
+fn diff_values(
+    changes: &mut Vec<Change>,
+    old: &BTreeMap<String, String>,
+    new: &BTreeMap<String, String>,
+) {
+    let keys: BTreeSet<&String> = old.keys().chain(new.keys()).collect();
+    for key in keys {
+        let old_value = old.get(key).map(String::as_str).unwrap_or("(none)");
+        let new_value = new.get(key).map(String::as_str).unwrap_or("(none)");
+        if old_value != new_value {
+            changes.push(Change {
+                field: key.clone(),
+                old_value: old_value.to_string(),
+                new_value: new_value.to_string(),
+            });
+        }
+    }
+}
+
+impl CompareDiff {
+    /// Compute the diff between two snapshots
+    fn compute(baseline: &ImageSnapshot, target: &ImageSnapshot, options: &CompareOptions) -> Self {
+        let mut diff = CompareDiff::default();
+
+        diff_values(&mut diff.os_changes, &baseline.os, &target.os);
+
+        for (name, version) in &target.packages {
+            match baseline.packages.get(name) {
+                None => diff.package_changes.added.push(format!("{} {}", name, version)),
+                Some(old) if old != version => diff.package_changes.updated.push(PackageUpdate {
+                    name: name.clone(),
+                    old_version: old.clone(),
+                    new_version: version.clone(),
+                }),
+                Some(_) => {}
+            }
+        }
+        for (name, version) in &baseline.packages {
+            if !target.packages.contains_key(name) {
+                diff.package_changes.removed.push(format!("{} {}", name, version));
+            }
+        }
+
+        diff.service_changes.enabled = target.services.difference(&baseline.services).cloned().collect();
+        diff.service_changes.disabled = baseline.services.difference(&target.services).cloned().collect();
+
+        diff.user_changes.added = target.users.difference(&baseline.users).cloned().collect();
+        diff.user_changes.removed = baseline.users.difference(&target.users).cloned().collect();
+
+        diff_values(&mut diff.network_changes, &baseline.network, &target.network);
+        diff_values(&mut diff.config_changes, &baseline.config, &target.config);
+
+        for (path, stamp) in &target.files {
+            match baseline.files.get(path) {
+                None => diff.file_changes.added.push(path.clone()),
+                Some(old) if old.sha256 != stamp.sha256 => {
+                    diff.file_changes.modified.push(path.clone())
+                }
+                Some(old) if !options.ignore_timestamps && old.mtime != stamp.mtime => {
+                    diff.file_changes.modified.push(path.clone())
+                }
+                Some(_) => {}
+            }
+        }
+        for path in baseline.files.keys() {
+            if !target.files.contains_key(path) {
+                diff.file_changes.removed.push(path.clone());
+            }
+        }
+
+        diff
+    }
+
+    /// Total number of differences
+    fn total_changes(&self) -> usize {
+        self.os_changes.len()
+            + self.package_changes.added.len()
+            + self.package_changes.removed.len()
+            + self.package_changes.updated.len()
+            + self.service_changes.enabled.len()
+            + self.service_changes.disabled.len()
+            + self.user_changes.added.len()
+            + self.user_changes.removed.len()
+            + self.network_changes.len()
+            + self.config_changes.len()
+            + self.file_changes.added.len()
+            + self.file_changes.removed.len()
+            + self.file_changes.modified.len()
+    }
+
+    /// Review suggestions for the target image
+    fn recommendations(&self) -> Vec<String> {
+        let mut recommendations = Vec::new();
+
+        if !self.user_changes.added.is_empty() {
+            recommendations.push(format!(
+                "Review new user accounts: {}",
+                self.user_changes.added.join(", ")
+            ));
+        }
+        if !self.service_changes.enabled.is_empty() {
+            recommendations.push(format!(
+                "Confirm newly enabled services are expected: {}",
+                self.service_changes.enabled.join(", ")
+            ));
+        }
+        if !self.package_changes.added.is_empty() {
+            recommendations.push(format!(
+                "Audit {} package(s) not present in the baseline",
+                self.package_changes.added.len()
+            ));
+        }
+        if !self.package_changes.removed.is_empty() {
+            recommendations.push(format!(
+                "Check that {} removed package(s) are not required",
+                self.package_changes.removed.len()
+            ));
+        }
+        if self
+            .config_changes
+            .iter()
+            .any(|c| c.field.contains("sshd_config") || c.field.contains("sudoers"))
+        {
+            recommendations.push(
+                "SSH or sudo configuration differs from the baseline; review access policy"
+                    .to_string(),
+            );
+        }
+
+        recommendations
+    }
+}
+
+/// Collect a snapshot of one image using guestkit
+fn collect_snapshot(
+    path: &str,
+    options: &CompareOptions,
+    trace: &TraceContext,
+) -> WorkerResult<ImageSnapshot> {
+    use guestkit::Guestfs;
+
+    let mut g = Guestfs::new()
+        .map_err(|e| WorkerError::ExecutionError(format!("Failed to create Guestfs handle: {}", e)))?;
+
+    g.add_drive_ro(path)
+        .map_err(|e| WorkerError::ExecutionError(format!("Failed to add drive {}: {}", path, e)))?;
+
+    trace.in_span("guestfs.launch", || g.launch())
+        .map_err(|e| WorkerError::ExecutionError(format!("Failed to launch: {}", e)))?;
+
+    let inspected_oses = trace.in_span("guestfs.inspect", || g.inspect())
+        .map_err(|e| WorkerError::ExecutionError(format!("Failed to inspect OS: {}", e)))?;
+    let os_info = inspected_oses.first().ok_or_else(|| {
+        WorkerError::ExecutionError(format!("No operating system found in {}", path))
+    })?;
+
+    let mut snapshot = ImageSnapshot::default();
+    snapshot.os.insert("type".to_string(), os_info.os_type.clone());
+    snapshot.os.insert("distribution".to_string(), os_info.distro.clone());
+    snapshot.os.insert("product_name".to_string(), os_info.product_name.clone());
+    snapshot.os.insert(
+        "version".to_string(),
+        format!("{}.{}", os_info.major_version, os_info.minor_version),
+    );
+    snapshot.os.insert("arch".to_string(), os_info.arch.clone());
+    snapshot.os.insert("hostname".to_string(), os_info.hostname.clone());
+
+    let root = os_info.root.clone();
+    trace.in_span("guestfs.mount", || g.mount_ro(&root, "/"))
+        .map_err(|e| WorkerError::ExecutionError(format!("Failed to mount root: {}", e)))?;
+
+    if options.compare_packages {
+        if let Ok(info) = g.inspect_packages(&root) {
+            snapshot.packages = info
+                .packages
+                .into_iter()
+                .map(|p| (p.name, p.version))
+                .collect();
+        }
+    }
+
+    if let Ok(users) = g.inspect_users(&root) {
+        snapshot.users = users
+            .into_iter()
+            .filter(|u| {
+                let uid: i32 = u.uid.parse().unwrap_or(0);
+                (1000..65534).contains(&uid)
+            })
+            .map(|u| u.username)
+            .collect();
+    }
+
+    if let Ok(services) = g.inspect_systemd_services(&root) {
+        snapshot.services = services.into_iter().map(|s| s.name).collect();
+    }
+
+    if let Ok(interfaces) = g.inspect_network(&root) {
+        snapshot.network = interfaces
+            .into_iter()
+            .map(|i| (i.name, i.ip_address.join(", ")))
+            .collect();
+    }
+
+    if options.compare_config {
+        if let Ok(timezone) = g.inspect_timezone(&root) {
+            snapshot.config.insert("timezone".to_string(), timezone);
+        }
+        if let Ok(selinux) = g.inspect_selinux(&root) {
+            snapshot.config.insert("selinux".to_string(), selinux);
+        }
+        for file in CONFIG_FILES {
+            if g.is_file(file).unwrap_or(false) {
+                if let Ok(sum) = g.checksum("sha256", file) {
+                    snapshot.config.insert(file.to_string(), sum);
+                }
+            }
+        }
+    }
+
+    if options.compare_files {
+        for dir in FILE_ROOTS {
+            if !g.is_dir(dir).unwrap_or(false) {
+                continue;
+            }
+            let files = g.find(dir).unwrap_or_default();
+            for file in files {
+                let file = format!("{}{}", dir, file);
+                let sha256 = match g.checksum("sha256", &file) {
+                    Ok(sum) => sum,
+                    Err(_) => continue,
+                };
+                let mtime = g.stat(&file).map(|s| s.mtime).unwrap_or(0);
+                snapshot.files.insert(file, FileStamp { sha256, mtime });
+            }
+        }
+    }
+
+    let _ = g.umount_all();
+    let _ = g.shutdown();
+
+    Ok(snapshot)
+}
+
+/// Guestkit compare handler
+pub struct CompareHandler;
+
+impl CompareHandler {
+    /// Create a new compare handler
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Snapshot one image on a blocking thread
+    async fn snapshot(
+        &self,
+        context: &HandlerContext,
+        path: String,
+        options: &CompareOptions,
+    ) -> WorkerResult<ImageSnapshot> {
+        let options = options.clone();
+        let trace = context.trace.clone();
+
+        tokio::task::spawn_blocking(move || collect_snapshot(&path, &options, &trace))
+            .await
+            .map_err(|e| WorkerError::ExecutionError(format!("Task join error: {}", e)))?
+    }
+
+    /// Compare the baseline and target images
+    async fn compare_images(
+        &self,
+        context: &HandlerContext,
+        payload: &ComparePayload,
+    ) -> WorkerResult<HandlerResult> {
+        context.report_progress("validation", Some(5), "Validating images").await?;
+        let baseline_path = super::resolve_image(
+            context,
+            &payload.baseline.path,
+            payload.baseline.checksum.as_deref(),
+        )
+        .await?;
+        let target_path = super::resolve_image(
+            context,
+            &payload.target.path,
+            payload.target.checksum.as_deref(),
+        )
+        .await?;
+
+        context.checkpoint().await?;
+        context.report_progress("baseline", Some(10), "Inspecting baseline image").await?;
+        let baseline = self.snapshot(context, baseline_path, &payload.options).await?;
+
+        context.checkpoint().await?;
+        context.report_progress("target", Some(50), "Inspecting target image").await?;
+        let target = self.snapshot(context, target_path, &payload.options).await?;
+
+        context.checkpoint().await?;
+        context.report_progress("diff", Some(90), "Computing differences").await?;
+
+        let output = payload.output.clone().unwrap_or_default();
+        let diff = CompareDiff::compute(&baseline, &target, &payload.options);
+        let total_changes = diff.total_changes();
+
+        let mut report = serde_json::json!({
+            "version": "1.0",
+            "baseline": payload.baseline,
+            "target": payload.target,
+            "compared_at": chrono::Utc::now().to_rfc3339(),
+            "total_changes": total_changes,
+            "diff": diff,
+        });
+        if output.include_recommendations {
+            report["recommendations"] = serde_json::json!(diff.recommendations());
+        }
+
+        let output_file = self.write_output(context, &report, &output).await?;
+
+        context
+            .report_progress(
+                "complete",
+                Some(100),
+                format!("Found {} difference(s)", total_changes),
+            )
+            .await?;
+
+        Ok(HandlerResult::new()
+            .with_output(output_file.clone())
+            .with_artifact(output_file)
+            .with_data(report))
+    }
+
+    /// Write the diff report to the requested destination
+    async fn write_output(
+        &self,
+        context: &HandlerContext,
+        report: &serde_json::Value,
+        output: &OutputSpec,
+    ) -> WorkerResult<String> {
+        let content = match output.format.as_str() {
+            "json" => serde_json::to_string_pretty(report)?,
+            "yaml" => serde_yaml::to_string(report)
+                .map_err(|e| WorkerError::ExecutionError(format!("YAML serialization failed: {}", e)))?,
+            _ => {
+                return Err(WorkerError::ExecutionError(
+                    format!("Unsupported output format: {}", output.format)
+                ));
+            }
+        };
+
+        let destination = match output.destination {
+            Some(ref destination) => std::path::PathBuf::from(destination),
+            None => context
+                .work_dir
+                .join(format!("{}-compare.{}", context.job_id, output.format)),
+        };
+
+        if let Some(parent) = destination.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        tokio::fs::write(&destination, content).await?;
+
+        Ok(destination.to_string_lossy().to_string())
+    }
+}
+
+impl Default for CompareHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl OperationHandler for CompareHandler {
+    fn name(&self) -> &str {
+        "guestkit-compare"
+    }
+
+    fn operations(&self) -> Vec<String> {
+        vec!["guestkit.compare".to_string()]
+    }
+
+    async fn validate(&self, payload: &Payload) -> WorkerResult<()> {
+        let compare_payload: ComparePayload = serde_json::from_value(payload.data.clone())
+            .map_err(|e| WorkerError::ExecutionError(
+                format!("Invalid compare payload: {}", e)
+            ))?;
+
+        if compare_payload.baseline.path.is_empty() || compare_payload.target.path.is_empty() {
+            return Err(WorkerError::ExecutionError(
+                "Baseline and target image paths cannot be empty".to_string()
+            ));
+        }
+
+        if compare_payload.baseline.path == compare_payload.target.path {
+            return Err(WorkerError::ExecutionError(
+                "Baseline and target must be different images".to_string()
+            ));
+        }
+
+        if let Some(ref output) = compare_payload.output {
+            if !["json", "yaml"].contains(&output.format.as_str()) {
+                return Err(WorkerError::ExecutionError(
+                    format!("Unsupported output format: {}", output.format)
+                ));
+            }
+        }
+
+        Ok(())
+    }
+
+    async fn execute(
+        &self,
+        context: HandlerContext,
+        payload: Payload,
+    ) -> WorkerResult<HandlerResult> {
+        log::info!("Starting image comparison for job {}", context.job_id);
+
+        let compare_payload: ComparePayload = serde_json::from_value(payload.data)
+            .map_err(|e| WorkerError::ExecutionError(
+                format!("Failed to parse compare payload: {}", e)
+            ))?;
+
+        self.compare_images(&context, &compare_payload).await
+    }
+
+    async fn cleanup(&self, context: &HandlerContext) -> WorkerResult<()> {
+        log::debug!("Cleanup for job {}", context.job_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(data: serde_json::Value) -> Payload {
+        Payload {
+            payload_type: "guestkit.compare.v1".to_string(),
+            data,
+        }
+    }
+
+    fn snapshot(packages: &[(&str, &str)], users: &[&str], services: &[&str]) -> ImageSnapshot {
+        ImageSnapshot {
+            packages: packages
+                .iter()
+                .map(|(n, v)| (n.to_string(), v.to_string()))
+                .collect(),
+            users: users.iter().map(|u| u.to_string()).collect(),
+            services: services.iter().map(|s| s.to_string()).collect(),
+            ..Default::default()
+        }
+    }
+
+    #[tokio::test]
+    async fn test_compare_handler_operations() {
+        let handler = CompareHandler::new();
+        assert_eq!(handler.operations(), vec!["guestkit.compare"]);
+        assert_eq!(handler.name(), "guestkit-compare");
+    }
+
+    #[tokio::test]
+    async fn test_compare_handler_validation() {
+        let handler = CompareHandler::new();
+
+        let valid = payload(serde_json::json!({
+            "baseline": { "path": "/vms/base.qcow2" },
+            "target": { "path": "/vms/web.qcow2" }
+        }));
+        assert!(handler.validate(&valid).await.is_ok());
+
+        let same = payload(serde_json::json!({
+            "baseline": { "path": "/vms/web.qcow2" },
+            "target": { "path": "/vms/web.qcow2" }
+        }));
+        assert!(handler.validate(&same).await.is_err());
+
+        let bad_format = payload(serde_json::json!({
+            "baseline": { "path": "/vms/base.qcow2" },
+            "target": { "path": "/vms/web.qcow2" },
+            "output": { "format": "xml" }
+        }));
+        assert!(handler.validate(&bad_format).await.is_err());
+    }
+
+    #[test]
+    fn test_compare_diff() {
+        let baseline = snapshot(
+            &[("openssl", "3.0.2"), ("telnet", "0.17")],
+            &["alice"],
+            &["sshd"],
+        );
+        let target = snapshot(
+            &[("openssl", "3.0.13"), ("nginx", "1.24")],
+            &["alice", "deploy"],
+            &["sshd", "nginx"],
+        );
+
+        let diff = CompareDiff::compute(&baseline, &target, &CompareOptions::default());
+        assert_eq!(diff.package_changes.added, vec!["nginx 1.24"]);
+        assert_eq!(diff.package_changes.removed, vec!["telnet 0.17"]);
+        assert_eq!(diff.package_changes.updated.len(), 1);
+        assert_eq!(diff.package_changes.updated[0].new_version, "3.0.13");
+        assert_eq!(diff.user_changes.added, vec!["deploy"]);
+        assert_eq!(diff.service_changes.enabled, vec!["nginx"]);
+        assert_eq!(diff.total_changes(), 5);
+        assert!(!diff.recommendations().is_empty());
+    }
+
+    #[test]
+    fn test_compare_diff_timestamps() {
+        let mut baseline = ImageSnapshot::default();
+        let mut target = ImageSnapshot::default();
+        let stamp = |mtime| FileStamp { sha256: "abc".to_string(), mtime };
+        baseline.files.insert("/etc/motd".to_string(), stamp(1));
+        target.files.insert("/etc/motd".to_string(), stamp(2));
+
+        let diff = CompareDiff::compute(&baseline, &target, &CompareOptions::default());
+        assert!(diff.file_changes.modified.is_empty());
+
+        let options = CompareOptions { ignore_timestamps: false, ..Default::default() };
+        let diff = CompareDiff::compute(&baseline, &target, &options);
+        assert_eq!(diff.file_changes.modified, vec!["/etc/motd"]);
+    }
+}
@@ -0,0 +1,472 @@
+//! Guestkit convert handler - Disk format conversion
+//!
+//! Progress is reported per whole percent of the image converted, not per
+//! cluster: `qemu-img convert -p` only reports percentages of the whole
+//! image, and the native raw copy's per-chunk progress is reported at the
+//! same steps. `bytes_processed` in the progress details is that share of
+//! the virtual size.
+
+use async_trait::async_trait;
+use guestkit::converters::{CopyOptions, DiskConverter, IoPriority};
+use guestkit::publish::{ImageProperties, PublishTarget, Published};
+use guestkit_job_spec::payloads::{ConvertPayload, CONVERT_TARGET_FORMATS};
+use guestkit_job_spec::{Constraints, Payload};
+use std::path::PathBuf;
+use crate::error::{WorkerError, WorkerResult};
+use crate::handler::{OperationHandler, HandlerContext, HandlerResult};
+
+/// Operation served by this handler
+const OPERATION: &str = "guestkit.convert";
+
+/// Guestkit convert handler
+pub struct ConvertHandler {
+    converter: std::sync::Arc<DiskConverter>,
+}
+
+impl ConvertHandler {
+    /// Create a new convert handler
+    pub fn new() -> Self {
+        Self {
+            converter: std::sync::Arc::new(DiskConverter::new()),
+        }
+    }
+
+    /// Create a convert handler using a specific qemu-img binary
+    pub fn with_qemu_img_path(path: impl Into<PathBuf>) -> Self {
+        Self {
+            converter: std::sync::Arc::new(DiskConverter::with_qemu_img_path(path.into())),
+        }
+    }
+
+    /// Read virtual size of an image via qemu-img info
+    async fn virtual_size(&self, path: &str) -> WorkerResult<Option<u64>> {
+        let converter = self.converter.clone();
+        let path = path.to_string();
+
+        let info = tokio::task::spawn_blocking(move || converter.get_info(&path))
+            .await
+            .map_err(|e| WorkerError::ExecutionError(format!("Task join error: {}", e)))?
+            .map_err(|e| WorkerError::ExecutionError(format!("Failed to read image info: {}", e)))?;
+
+        Ok(info.get("virtual-size").and_then(|v| v.as_u64()))
+    }
+
+    /// Run the conversion, forwarding its percentage progress to the tracker
+    async fn run_conversion(
+        &self,
+        context: &HandlerContext,
+        payload: &ConvertPayload,
+        virtual_size: Option<u64>,
+    ) -> WorkerResult<guestkit::core::ConversionResult> {
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<f64>();
+        let converter = (*self.converter)
+            .clone()
+            .with_copy_options(copy_options(&context.constraints)?);
+        let cancel = context.cancel.clone();
+        let source = PathBuf::from(&payload.source.path);
+        let target = PathBuf::from(&payload.target.path);
+        let format = payload.target.format.clone();
+        let compress = payload.target.compression;
+
+        let task = tokio::task::spawn_blocking(move || {
+            converter.convert_with_progress(&source, &target, &format, compress, true, |percent| {
+                let _ = tx.send(percent);
+                !cancel.is_cancelled()
+            })
+        });
+
+        // Channel closes when the conversion thread finishes
+        let mut last_reported = None;
+        while let Some(percent) = rx.recv().await {
+            let whole = percent.clamp(0.0, 100.0) as u8;
+            if last_reported == Some(whole) {
+                continue;
+            }
+            last_reported = Some(whole);
+
+            let mut details = serde_json::json!({ "percent": percent });
+            if let Some(size) = virtual_size {
+                let processed = (size as f64 * percent / 100.0) as u64;
+                details["virtual_size"] = serde_json::json!(size);
+                details["bytes_processed"] = serde_json::json!(processed);
+                context.record_bytes_processed(OPERATION, processed);
+            }
+
+            context
+                .progress
+                .report_with_details(
+                    "conversion",
+                    Some(10 + whole * 4 / 5),
+                    format!("Converted {:.1}%", percent),
+                    details,
+                )
+                .await?;
+        }
+
+        let result = task
+            .await
+            .map_err(|e| WorkerError::ExecutionError(format!("Task join error: {}", e)))?;
+
+        context.checkpoint().await?;
+
+        let result = result
+            .map_err(|e| WorkerError::ExecutionError(format!("Conversion failed: {}", e)))?;
+
+        if !result.success {
+            return Err(WorkerError::ExecutionError(format!(
+                "qemu-img convert failed: {}",
+                result.error.as_deref().unwrap_or("unknown error").trim()
+            )));
+        }
+
+        Ok(result)
+    }
+
+    /// Check the converted image has the requested format and source size
+    async fn verify_output(
+        &self,
+        payload: &ConvertPayload,
+        source_size: Option<u64>,
+    ) -> WorkerResult<()> {
+        let converter = self.converter.clone();
+        let target = payload.target.path.clone();
+
+        let info = tokio::task::spawn_blocking(move || converter.get_info(&target))
+            .await
+            .map_err(|e| WorkerError::ExecutionError(format!("Task join error: {}", e)))?
+            .map_err(|e| WorkerError::ExecutionError(format!("Failed to read converted image: {}", e)))?;
+
+        let format = info.get("format").and_then(|v| v.as_str()).unwrap_or("");
+        if format != payload.target.format {
+            return Err(WorkerError::ExecutionError(format!(
+                "Converted image has format {}, expected {}",
+                format, payload.target.format
+            )));
+        }
+
+        let target_size = info.get("virtual-size").and_then(|v| v.as_u64());
+        if source_size.is_some() && target_size != source_size {
+            return Err(WorkerError::ExecutionError(format!(
+                "Converted image virtual size {:?} does not match source {:?}",
+                target_size, source_size
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Upload the converted image to each `options.publish` target
+    async fn publish_output(
+        &self,
+        context: &HandlerContext,
+        payload: &ConvertPayload,
+    ) -> WorkerResult<Vec<Published>> {
+        let targets = parse_publish_targets(&payload.options.publish)?;
+        if targets.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        context.report_progress("publish", Some(96), "Inspecting converted image").await?;
+        let image = PathBuf::from(&payload.target.path);
+        let properties = {
+            let image = image.clone();
+            tokio::task::spawn_blocking(move || ImageProperties::detect(&image))
+                .await
+                .map_err(|e| WorkerError::ExecutionError(format!("Task join error: {}", e)))?
+                .map_err(|e| WorkerError::ExecutionError(format!("Failed to inspect converted image: {}", e)))?
+        };
+
+        let mut published = Vec::new();
+        for target in targets {
+            context.checkpoint().await?;
+            let name = target.to_string();
+            context.report_progress("publish", Some(97), format!("Publishing to {}", name)).await?;
+
+            let image = image.clone();
+            let format = payload.target.format.clone();
+            let properties = properties.clone();
+            let result = tokio::task::spawn_blocking(move || target.publish(&image, &format, &properties))
+                .await
+                .map_err(|e| WorkerError::ExecutionError(format!("Task join error: {}", e)))?
+                .map_err(|e| WorkerError::ExecutionError(format!("Failed to publish to {}: {}", name, e)))?;
+            log::info!("Published {} to {} as {}", payload.target.path, name, result.id);
+            published.push(result);
+        }
+
+        Ok(published)
+    }
+
+    /// Perform disk conversion
+    async fn convert_disk(
+        &self,
+        context: &HandlerContext,
+        payload: &ConvertPayload,
+    ) -> WorkerResult<HandlerResult> {
+        context.report_progress("validation", Some(5), "Validating source image").await?;
+
+        let target_path = std::path::Path::new(&payload.target.path);
+        if target_path.exists() && !payload.options.overwrite {
+            return Err(WorkerError::ExecutionError(format!(
+                "Target already exists: {} (set options.overwrite to replace it)",
+                payload.target.path
+            )));
+        }
+
+        // Remote sources are downloaded to scratch space first
+        let source = super::resolve_image(
+            context,
+            &payload.source.path,
+            payload.source.checksum.as_deref(),
+        )
+        .await?;
+        let mut local_payload = payload.clone();
+        local_payload.source.path = source.clone();
+
+        if let Some(ref compression_type) = payload.target.compression_type {
+            log::warn!(
+                "compression_type {} not supported by qemu-img convert wrapper, using default",
+                compression_type
+            );
+        }
+        if !payload.options.preserve_sparse {
+            log::warn!("preserve_sparse=false ignored; qemu-img always writes sparse output");
+        }
+
+        if let Some(parent) = target_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+
+        let virtual_size = self.virtual_size(&source).await?;
+        let source_bytes = tokio::fs::metadata(&source).await?.len();
+
+        context.checkpoint().await?;
+        context.report_progress("conversion", Some(10), "Starting conversion").await?;
+
+        let result = self.run_conversion(context, &local_payload, virtual_size).await?;
+
+        context.record_disk_read(source_bytes);
+        context.record_disk_write(result.output_size);
+
+        if payload.options.verify_after_convert {
+            context.report_progress("verification", Some(95), "Verifying converted image").await?;
+            self.verify_output(payload, virtual_size).await?;
+        }
+
+        let published = self.publish_output(context, payload).await?;
+
+        // Conversion report registered as an artifact alongside the image
+        let mut summary = serde_json::json!({
+            "source": {
+                "path": payload.source.path,
+                "format": result.source_format.as_str(),
+                "size_bytes": source_bytes,
+            },
+            "target": {
+                "path": payload.target.path,
+                "format": result.output_format.as_str(),
+                "size_bytes": result.output_size,
+                "compressed": payload.target.compression,
+            },
+            "virtual_size": virtual_size,
+            "duration_secs": result.duration_secs,
+            "verified": payload.options.verify_after_convert,
+        });
+        if !published.is_empty() {
+            summary["published"] = serde_json::to_value(&published)?;
+        }
+
+        tokio::fs::create_dir_all(&context.work_dir).await?;
+        let report_file = context.work_dir.join(format!("{}-convert.json", context.job_id));
+        tokio::fs::write(&report_file, serde_json::to_string_pretty(&summary)?).await?;
+
+        context.report_progress("complete", Some(100), "Conversion complete").await?;
+
+        Ok(HandlerResult::new()
+            .with_output(payload.target.path.clone())
+            .with_artifact(report_file.to_string_lossy().to_string())
+            .with_data(summary))
+    }
+}
+
+impl Default for ConvertHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl OperationHandler for ConvertHandler {
+    fn name(&self) -> &str {
+        "guestkit-convert"
+    }
+
+    fn operations(&self) -> Vec<String> {
+        vec![OPERATION.to_string()]
+    }
+
+    async fn validate(&self, payload: &Payload) -> WorkerResult<()> {
+        let convert_payload: ConvertPayload = serde_json::from_value(payload.data.clone())
+            .map_err(|e| WorkerError::ExecutionError(
+                format!("Invalid convert payload: {}", e)
+            ))?;
+
+        if convert_payload.source.path.is_empty() || convert_payload.target.path.is_empty() {
+            return Err(WorkerError::ExecutionError(
+                "Source and target paths cannot be empty".to_string()
+            ));
+        }
+
+        if convert_payload.source.path == convert_payload.target.path {
+            return Err(WorkerError::ExecutionError(
+                "Source and target paths must differ".to_string()
+            ));
+        }
+
+        if !CONVERT_TARGET_FORMATS.contains(&convert_payload.target.format.as_str()) {
+            return Err(WorkerError::ExecutionError(
+                format!("Unsupported target format: {}", convert_payload.target.format)
+            ));
+        }
+
+        if convert_payload.target.compression && convert_payload.target.format != "qcow2" {
+            return Err(WorkerError::ExecutionError(
+                "Compression is only supported for qcow2 targets".to_string()
+            ));
+        }
+
+        parse_publish_targets(&convert_payload.options.publish)?;
+
+        Ok(())
+    }
+
+    async fn execute(
+        &self,
+        context: HandlerContext,
+        payload: Payload,
+    ) -> WorkerResult<HandlerResult> {
+        log::info!("Starting disk conversion for job {}", context.job_id);
+
+        let convert_payload: ConvertPayload = serde_json::from_value(payload.data)
+            .map_err(|e| WorkerError::ExecutionError(
+                format!("Failed to parse convert payload: {}", e)
+            ))?;
+
+        self.convert_disk(&context, &convert_payload).await
+    }
+
+    async fn cleanup(&self, context: &HandlerContext) -> WorkerResult<()> {
+        log::debug!("Cleanup for job {}", context.job_id);
+        Ok(())
+    }
+}
+
+/// Bandwidth limit and I/O priority of the copy, from the job's constraints
+fn copy_options(constraints: &Constraints) -> WorkerResult<CopyOptions> {
+    let io_priority = constraints
+        .io_priority
+        .as_deref()
+        .map(str::parse::<IoPriority>)
+        .transpose()
+        .map_err(|e| WorkerError::ExecutionError(format!("Invalid constraints.io_priority: {}", e)))?;
+
+    Ok(CopyOptions {
+        bwlimit: constraints.max_io_bandwidth_mb_per_sec.map(|mb| mb * 1_000_000),
+        io_priority,
+        ..CopyOptions::default()
+    })
+}
+
+/// Parse `options.publish` target URIs
+fn parse_publish_targets(targets: &[String]) -> WorkerResult<Vec<PublishTarget>> {
+    targets
+        .iter()
+        .map(|target| {
+            target.parse().map_err(|e| {
+                WorkerError::ExecutionError(format!("Invalid publish target: {}", e))
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(data: serde_json::Value) -> Payload {
+        Payload {
+            payload_type: "guestkit.convert.v1".to_string(),
+            data,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_convert_handler_operations() {
+        let handler = ConvertHandler::new();
+        assert_eq!(handler.operations(), vec!["guestkit.convert"]);
+        assert_eq!(handler.name(), "guestkit-convert");
+    }
+
+    #[test]
+    fn test_copy_options_from_constraints() {
+        let options = copy_options(&Constraints::default()).unwrap();
+        assert_eq!(options, CopyOptions::default());
+
+        let options = copy_options(&Constraints {
+            max_io_bandwidth_mb_per_sec: Some(50),
+            io_priority: Some("best-effort:7".to_string()),
+            ..Default::default()
+        })
+        .unwrap();
+        assert_eq!(options.bwlimit, Some(50_000_000));
+        assert_eq!(options.io_priority, Some(IoPriority::BestEffort(7)));
+
+        assert!(copy_options(&Constraints {
+            io_priority: Some("realtime".to_string()),
+            ..Default::default()
+        })
+        .is_err());
+    }
+
+    #[tokio::test]
+    async fn test_convert_handler_validation() {
+        let handler = ConvertHandler::new();
+
+        let valid = payload(serde_json::json!({
+            "source": { "path": "/vms/source.vmdk", "format": "vmdk" },
+            "target": { "path": "/vms/target.qcow2", "format": "qcow2", "compression": true }
+        }));
+        assert!(handler.validate(&valid).await.is_ok());
+
+        let bad_format = payload(serde_json::json!({
+            "source": { "path": "/vms/source.vmdk" },
+            "target": { "path": "/vms/target.iso", "format": "iso" }
+        }));
+        assert!(handler.validate(&bad_format).await.is_err());
+
+        let raw_compressed = payload(serde_json::json!({
+            "source": { "path": "/vms/source.vmdk" },
+            "target": { "path": "/vms/target.img", "format": "raw", "compression": true }
+        }));
+        assert!(handler.validate(&raw_compressed).await.is_err());
+
+        let same_path = payload(serde_json::json!({
+            "source": { "path": "/vms/disk.qcow2" },
+            "target": { "path": "/vms/disk.qcow2", "format": "qcow2" }
+        }));
+        assert!(handler.validate(&same_path).await.is_err());
+
+        let published = payload(serde_json::json!({
+            "source": { "path": "/vms/source.vmdk" },
+            "target": { "path": "/vms/target.qcow2", "format": "qcow2" },
+            "options": { "publish": ["proxmox://pve.example.com/pve1/local?vmid=120"] }
+        }));
+        assert!(handler.validate(&published).await.is_ok());
+
+        let bad_publish = payload(serde_json::json!({
+            "source": { "path": "/vms/source.vmdk" },
+            "target": { "path": "/vms/target.qcow2", "format": "qcow2" },
+            "options": { "publish": ["proxmox://pve.example.com/pve1"] }
+        }));
+        assert!(handler.validate(&bad_publish).await.is_err());
+    }
+}
@@ -0,0 +1,348 @@
+//! Guestkit fix handler - Apply fix plans to VM disks
+
+use async_trait::async_trait;
+use guestkit::plan::{FixPlan, OperationOutcome, PlanApplicator};
+use guestkit_job_spec::payloads::{FixImageSpec, FixPayload};
+use guestkit_job_spec::Payload;
+use crate::error::{WorkerError, WorkerResult};
+use crate::handler::{OperationHandler, HandlerContext, HandlerResult};
+
+/// Decode the fix plan embedded in a payload
+fn fix_plan(payload: &FixPayload) -> WorkerResult<FixPlan> {
+    let plan = match payload.plan {
+        // YAML is a superset of JSON, so one parser covers both documents
+        serde_json::Value::String(ref document) => serde_yaml::from_str(document)
+            .map_err(|e| WorkerError::ExecutionError(format!("Invalid fix plan: {}", e)))?,
+        ref value => serde_json::from_value(value.clone())
+            .map_err(|e| WorkerError::ExecutionError(format!("Invalid fix plan: {}", e)))?,
+    };
+    Ok(plan)
+}
+
+/// Guestkit fix handler
+pub struct FixHandler;
+
+impl FixHandler {
+    /// Create a new fix handler
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Copy the image aside before modifying it
+    async fn backup_image(
+        &self,
+        context: &HandlerContext,
+        image: &FixImageSpec,
+    ) -> WorkerResult<String> {
+        let backup_path = match image.backup_path {
+            Some(ref path) => std::path::PathBuf::from(path),
+            None => {
+                let name = std::path::Path::new(&image.path)
+                    .file_name()
+                    .map(|n| n.to_string_lossy().to_string())
+                    .unwrap_or_else(|| "image".to_string());
+                context.work_dir.join(format!("{}-backup-{}", context.job_id, name))
+            }
+        };
+
+        if let Some(parent) = backup_path.parent() {
+            tokio::fs::create_dir_all(parent).await?;
+        }
+        let bytes = tokio::fs::copy(&image.path, &backup_path).await?;
+        context.record_disk_read(bytes);
+        context.record_disk_write(bytes);
+
+        Ok(backup_path.to_string_lossy().to_string())
+    }
+
+    /// Apply the plan, reporting progress per operation
+    async fn apply_plan(
+        &self,
+        context: &HandlerContext,
+        payload: &FixPayload,
+        plan: FixPlan,
+    ) -> WorkerResult<HandlerResult> {
+        context.report_progress("validation", Some(5), "Validating image and plan").await?;
+
+        if !std::path::Path::new(&payload.image.path).exists() {
+            return Err(WorkerError::ExecutionError(
+                format!("Image not found: {}", payload.image.path)
+            ));
+        }
+
+        if let Some(ref checksum) = payload.image.checksum {
+            context.report_progress("validation", Some(8), "Verifying image checksum").await?;
+            if !super::verify_checksum(&payload.image.path, checksum).await? {
+                context.record_checksum_verification("failure");
+                return Err(WorkerError::ExecutionError(format!(
+                    "Image checksum verification failed for {}",
+                    payload.image.path
+                )));
+            }
+            context.record_checksum_verification("success");
+        } else {
+            context.record_checksum_verification("skipped");
+        }
+
+        let rollback_dir = context.work_dir.join(format!("{}-rollback", context.job_id));
+        let applicator = PlanApplicator::new(payload.image.path.clone(), payload.options.dry_run)
+            .with_backup_dir(&rollback_dir);
+
+        let validation = applicator
+            .validate(&plan)
+            .map_err(|e| WorkerError::ExecutionError(format!("Plan validation failed: {}", e)))?;
+        if !validation.valid {
+            return Err(WorkerError::ExecutionError(format!(
+                "Invalid fix plan: {}",
+                validation.errors.join("; ")
+            )));
+        }
+        for warning in &validation.warnings {
+            log::warn!("Job {}: {}", context.job_id, warning);
+        }
+
+        let mut image_backup = None;
+        if payload.image.create_backup && !payload.options.dry_run {
+            context.report_progress("backup", Some(10), "Backing up image").await?;
+            image_backup = Some(self.backup_image(context, &payload.image).await?);
+        }
+
+        context.checkpoint().await?;
+        context.report_progress("fix", Some(15), "Applying fix plan").await?;
+
+        // Operation progress from the blocking applicator thread
+        let (tx, mut rx) = tokio::sync::mpsc::unbounded_channel::<(usize, String)>();
+        let cancel = context.cancel.clone();
+        let total = plan.operations.len().max(1);
+        let trace = context.trace.clone();
+
+        let task = tokio::task::spawn_blocking(move || {
+            let _span = trace.span("guestfs.apply_plan");
+            applicator.apply_with_progress(&plan, |started, op| {
+                let _ = tx.send((started, op.description.clone()));
+                !cancel.is_cancelled()
+            })
+        });
+
+        while let Some((started, description)) = rx.recv().await {
+            let percent = 15 + (started * 75 / total) as u8;
+            context
+                .progress
+                .report_with_details(
+                    "fix",
+                    Some(percent),
+                    description,
+                    serde_json::json!({ "operation": started + 1, "total": total }),
+                )
+                .await?;
+        }
+
+        let result = task
+            .await
+            .map_err(|e| WorkerError::ExecutionError(format!("Task join error: {}", e)))?;
+
+        context.checkpoint().await?;
+
+        let result = result
+            .map_err(|e| WorkerError::ExecutionError(format!("Fix plan failed: {:#}", e)))?;
+
+        // Per-operation results registered as an artifact
+        let report = serde_json::json!({
+            "image": payload.image.path,
+            "image_backup": image_backup,
+            "dry_run": payload.options.dry_run,
+            "result": result,
+        });
+
+        tokio::fs::create_dir_all(&context.work_dir).await?;
+        let report_file = context.work_dir.join(format!("{}-fix.json", context.job_id));
+        tokio::fs::write(&report_file, serde_json::to_string_pretty(&report)?).await?;
+
+        if payload.options.fail_on_error && result.operations_failed > 0 {
+            let failed: Vec<String> = result
+                .operations
+                .iter()
+                .filter(|op| op.outcome == OperationOutcome::Failed)
+                .map(|op| format!("{}: {}", op.id, op.message))
+                .collect();
+            return Err(WorkerError::ExecutionError(format!(
+                "{} (report: {}): {}",
+                result.message,
+                report_file.display(),
+                failed.join("; ")
+            )));
+        }
+
+        context.report_progress("complete", Some(100), result.message.clone()).await?;
+
+        let mut handler_result = HandlerResult::new()
+            .with_output(payload.image.path.clone())
+            .with_artifact(report_file.to_string_lossy().to_string());
+        if let Some(ref rollback_file) = result.rollback_file {
+            handler_result = handler_result.with_artifact(rollback_file.clone());
+        }
+        if let Some(ref backup) = image_backup {
+            handler_result = handler_result.with_artifact(backup.clone());
+        }
+
+        Ok(handler_result.with_data(report))
+    }
+}
+
+impl Default for FixHandler {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[async_trait]
+impl OperationHandler for FixHandler {
+    fn name(&self) -> &str {
+        "guestkit-fix"
+    }
+
+    fn operations(&self) -> Vec<String> {
+        vec!["guestkit.fix".to_string()]
+    }
+
+    async fn validate(&self, payload: &Payload) -> WorkerResult<()> {
+        let fix_payload: FixPayload = serde_json::from_value(payload.data.clone())
+            .map_err(|e| WorkerError::ExecutionError(
+                format!("Invalid fix payload: {}", e)
+            ))?;
+
+        if fix_payload.image.path.is_empty() {
+            return Err(WorkerError::ExecutionError(
+                "Image path cannot be empty".to_string()
+            ));
+        }
+
+        // Fixes modify the image in place, so it must be reachable on disk
+        if crate::artifacts::is_remote(&fix_payload.image.path) {
+            return Err(WorkerError::ExecutionError(format!(
+                "Fix requires a local image path, got {}",
+                fix_payload.image.path
+            )));
+        }
+
+        let plan = fix_plan(&fix_payload)?;
+        if plan.operations.is_empty() {
+            return Err(WorkerError::ExecutionError(
+                "Fix plan has no operations".to_string()
+            ));
+        }
+
+        guestkit::plan::apply::execution_order(&plan)
+            .map_err(|e| WorkerError::ExecutionError(format!("Invalid fix plan: {}", e)))?;
+
+        Ok(())
+    }
+
+    async fn execute(
+        &self,
+        context: HandlerContext,
+        payload: Payload,
+    ) -> WorkerResult<HandlerResult> {
+        log::info!("Starting fix plan for job {}", context.job_id);
+
+        let fix_payload: FixPayload = serde_json::from_value(payload.data)
+            .map_err(|e| WorkerError::ExecutionError(
+                format!("Failed to parse fix payload: {}", e)
+            ))?;
+        let plan = fix_plan(&fix_payload)?;
+
+        self.apply_plan(&context, &fix_payload, plan).await
+    }
+
+    async fn cleanup(&self, context: &HandlerContext) -> WorkerResult<()> {
+        log::debug!("Cleanup for job {}", context.job_id);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn payload(data: serde_json::Value) -> Payload {
+        Payload {
+            payload_type: "guestkit.fix.v1".to_string(),
+            data,
+        }
+    }
+
+    fn plan_json() -> serde_json::Value {
+        serde_json::json!({
+            "version": "1.0",
+            "vm": "/vms/web.qcow2",
+            "generated": "2026-01-01T00:00:00Z",
+            "profile": "security",
+            "overall_risk": "low",
+            "estimated_duration": "1m",
+            "metadata": { "author": "test", "review_required": false, "reversible": true },
+            "operations": [
+                {
+                    "id": "ssh-root",
+                    "type": "file_edit",
+                    "file": "/etc/ssh/sshd_config",
+                    "changes": [{ "line": 0, "before": "PermitRootLogin yes", "after": "PermitRootLogin no" }],
+                    "priority": "high",
+                    "description": "Disable root login",
+                    "risk": "low",
+                    "reversible": true
+                },
+                {
+                    "id": "sshd-restart",
+                    "type": "service_operation",
+                    "service": "sshd",
+                    "restart": true,
+                    "priority": "medium",
+                    "description": "Restart sshd",
+                    "risk": "low",
+                    "reversible": true,
+                    "depends_on": ["ssh-root"]
+                }
+            ],
+            "post_apply": []
+        })
+    }
+
+    #[tokio::test]
+    async fn test_fix_handler_operations() {
+        let handler = FixHandler::new();
+        assert_eq!(handler.operations(), vec!["guestkit.fix"]);
+        assert_eq!(handler.name(), "guestkit-fix");
+    }
+
+    #[tokio::test]
+    async fn test_fix_handler_validation() {
+        let handler = FixHandler::new();
+
+        let valid = payload(serde_json::json!({
+            "image": { "path": "/vms/web.qcow2" },
+            "plan": plan_json()
+        }));
+        assert!(handler.validate(&valid).await.is_ok());
+
+        // Plan documents may be embedded as YAML/JSON text
+        let as_string = payload(serde_json::json!({
+            "image": { "path": "/vms/web.qcow2" },
+            "plan": plan_json().to_string()
+        }));
+        assert!(handler.validate(&as_string).await.is_ok());
+
+        let mut cyclic = plan_json();
+        cyclic["operations"][0]["depends_on"] = serde_json::json!(["sshd-restart"]);
+        let cyclic = payload(serde_json::json!({
+            "image": { "path": "/vms/web.qcow2" },
+            "plan": cyclic
+        }));
+        assert!(handler.validate(&cyclic).await.is_err());
+
+        let no_plan = payload(serde_json::json!({
+            "image": { "path": "/vms/web.qcow2" },
+            "plan": { "operations": [] }
+        }));
+        assert!(handler.validate(&no_plan).await.is_err());
+    }
+}
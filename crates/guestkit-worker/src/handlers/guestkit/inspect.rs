@@ -1,64 +1,12 @@
 //! Guestkit inspect handler - VM disk inspection
 
 use async_trait::async_trait;
+use guestkit_job_spec::payloads::{InspectPayload, OutputSpec};
 use guestkit_job_spec::Payload;
-use serde::{Deserialize, Serialize};
 use std::path::PathBuf;
 use crate::error::{WorkerError, WorkerResult};
 use crate::handler::{OperationHandler, HandlerContext, HandlerResult};
 
-/// Inspect operation payload
-#[derive(Debug, Clone, Deserialize, Serialize)]
-struct InspectPayload {
-    image: ImageSpec,
-    #[serde(default)]
-    options: InspectOptions,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    output: Option<OutputSpec>,
-}
-
-#[derive(Debug, Clone, Deserialize, Serialize)]
-struct ImageSpec {
-    path: String,
-    format: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    checksum: Option<String>,
-    #[serde(default = "default_true")]
-    read_only: bool,
-}
-
-fn default_true() -> bool {
-    true
-}
-
-#[derive(Debug, Clone, Deserialize, Serialize, Default)]
-struct InspectOptions {
-    #[serde(default)]
-    deep_scan: bool,
-    #[serde(default = "default_true")]
-    include_packages: bool,
-    #[serde(default = "default_true")]
-    include_services: bool,
-    #[serde(default = "default_true")]
-    include_users: bool,
-    #[serde(default = "default_true")]
-    include_network: bool,
-    #[serde(default = "default_true")]
-    include_security: bool,
-    #[serde(default = "default_true")]
-    include_storage: bool,
-    #[serde(default)]
-    include_databases: bool,
-}
-
-#[derive(Debug, Clone, Deserialize, Serialize)]
-struct OutputSpec {
-    format: String,
-    destination: String,
-    #[serde(skip_serializing_if = "Option::is_none")]
-    compression: Option<String>,
-}
-
 /// Guestkit inspect handler
 pub struct InspectHandler {
     /// Temporary directory for operations
@@ -75,66 +23,9 @@ impl InspectHandler {
 
     /// Verify image checksum if provided
     /// Supports format: "sha256:hexhash" or just "hexhash" (defaults to SHA256)
+    #[cfg(test)]
     async fn verify_checksum(&self, path: &str, expected: &str) -> WorkerResult<bool> {
-        use sha2::{Sha256, Digest};
-        use std::io::Read;
-
-        log::info!("Verifying checksum for image: {}", path);
-
-        // Parse checksum format
-        let (algorithm, expected_hash) = if expected.contains(':') {
-            let parts: Vec<&str> = expected.splitn(2, ':').collect();
-            if parts.len() != 2 {
-                return Err(WorkerError::ExecutionError(
-                    format!("Invalid checksum format: {}", expected)
-                ));
-            }
-            (parts[0].to_lowercase(), parts[1].to_lowercase())
-        } else {
-            // Default to SHA256 if no algorithm specified
-            ("sha256".to_string(), expected.to_lowercase())
-        };
-
-        // Only SHA256 is supported for now
-        if algorithm != "sha256" {
-            return Err(WorkerError::ExecutionError(
-                format!("Unsupported checksum algorithm: {}. Only 'sha256' is supported.", algorithm)
-            ));
-        }
-
-        // Open file and compute SHA256
-        let mut file = std::fs::File::open(path)
-            .map_err(|e| WorkerError::ExecutionError(
-                format!("Failed to open image for checksum verification: {}", e)
-            ))?;
-
-        let mut hasher = Sha256::new();
-        let mut buffer = vec![0u8; 8192]; // 8KB buffer for reading
-
-        loop {
-            let bytes_read = file.read(&mut buffer)
-                .map_err(|e| WorkerError::ExecutionError(
-                    format!("Failed to read image during checksum verification: {}", e)
-                ))?;
-
-            if bytes_read == 0 {
-                break;
-            }
-
-            hasher.update(&buffer[..bytes_read]);
-        }
-
-        let computed_hash = format!("{:x}", hasher.finalize());
-
-        log::debug!("Checksum verification - Expected: {}, Computed: {}", expected_hash, computed_hash);
-
-        if computed_hash != expected_hash {
-            log::error!("Checksum mismatch! Expected: {}, Got: {}", expected_hash, computed_hash);
-            return Ok(false);
-        }
-
-        log::info!("Checksum verification successful");
-        Ok(true)
+        super::verify_checksum(path, expected).await
     }
 
     /// Perform VM disk inspection
@@ -145,33 +36,23 @@ impl InspectHandler {
     ) -> WorkerResult<serde_json::Value> {
         context.report_progress("validation", Some(5), "Validating image").await?;
 
-        // Verify image exists
-        let image_path = std::path::Path::new(&payload.image.path);
-        if !image_path.exists() {
-            return Err(WorkerError::ExecutionError(
-                format!("Image not found: {}", payload.image.path)
-            ));
-        }
-
-        // Verify checksum if provided
-        if let Some(ref checksum) = payload.image.checksum {
-            context.report_progress("validation", Some(10), "Verifying image checksum").await?;
-            if !self.verify_checksum(&payload.image.path, checksum).await? {
-                context.record_checksum_verification("failure");
-                return Err(WorkerError::ExecutionError(
-                    format!("Image checksum verification failed for {}. The image may be corrupted or tampered with.", payload.image.path)
-                ));
-            }
-            context.record_checksum_verification("success");
-        } else {
-            context.record_checksum_verification("skipped");
-        }
-
+        // Fetch remote images and verify the checksum
+        let local_path = super::resolve_image(
+            context,
+            &payload.image.path,
+            payload.image.checksum.as_deref(),
+        )
+        .await?;
+        let mut local_payload = payload.clone();
+        local_payload.image.path = local_path;
+
+        context.checkpoint().await?;
         context.report_progress("inspection", Some(20), "Starting VM inspection").await?;
 
         // Perform real inspection using guestkit library
-        let inspection_result = self.real_inspection(&payload).await?;
+        let inspection_result = self.real_inspection(context, &local_payload).await?;
 
+        context.checkpoint().await?;
         context.report_progress("analysis", Some(80), "Analyzing results").await?;
 
         // Generate output
@@ -202,9 +83,19 @@ impl InspectHandler {
     }
 
     /// Real inspection using guestkit library
-    async fn real_inspection(&self, payload: &InspectPayload) -> WorkerResult<serde_json::Value> {
+    async fn real_inspection(
+        &self,
+        context: &HandlerContext,
+        payload: &InspectPayload,
+    ) -> WorkerResult<serde_json::Value> {
         // Run blocking guestkit operations in a separate thread
         let payload_clone = payload.clone();
+        let options = payload.options.clone().unwrap_or_default();
+        let trace = context.trace.clone();
+
+        // Checked between inspection phases; a blocking thread cannot wait
+        // out a pause, so pauses take effect once inspection returns
+        let phase_context = context.clone();
 
         tokio::task::spawn_blocking(move || -> WorkerResult<serde_json::Value> {
             use guestkit::Guestfs;
@@ -218,13 +109,15 @@ impl InspectHandler {
                 .map_err(|e| WorkerError::ExecutionError(format!("Failed to add drive: {}", e)))?;
 
             // Launch the VM
-            g.launch()
+            trace.in_span("guestfs.launch", || g.launch())
                 .map_err(|e| WorkerError::ExecutionError(format!("Failed to launch: {}", e)))?;
 
             // Inspect the OS
-            let inspected_oses = g.inspect()
+            let inspected_oses = trace.in_span("guestfs.inspect", || g.inspect())
                 .map_err(|e| WorkerError::ExecutionError(format!("Failed to inspect OS: {}", e)))?;
 
+            phase_context.check_cancelled()?;
+
             if inspected_oses.is_empty() {
                 return Err(WorkerError::ExecutionError("No operating system found in image".to_string()));
             }
@@ -254,11 +147,13 @@ impl InspectHandler {
             });
 
             // Mount the root filesystem
-            g.mount_ro(&os_info.root, "/")
+            trace.in_span("guestfs.mount", || g.mount_ro(&os_info.root, "/"))
                 .map_err(|e| WorkerError::ExecutionError(format!("Failed to mount root: {}", e)))?;
 
             // Collect packages if requested
-            if payload_clone.options.include_packages {
+            phase_context.check_cancelled()?;
+            if options.include_packages {
+                let _span = trace.span("guestfs.packages");
                 let packages = match os_info.package_format.as_str() {
                     "deb" => g.dpkg_list().ok(),
                     "rpm" => g.rpm_list().ok(),
@@ -275,7 +170,8 @@ impl InspectHandler {
             }
 
             // Collect services if requested
-            if payload_clone.options.include_services {
+            phase_context.check_cancelled()?;
+            if options.include_services {
                 if let Ok(services) = g.list_enabled_services() {
                     result["services"] = serde_json::json!({
                         "count": services.len(),
@@ -285,7 +181,8 @@ impl InspectHandler {
             }
 
             // Collect network interfaces if requested
-            if payload_clone.options.include_network {
+            phase_context.check_cancelled()?;
+            if options.include_network {
                 if let Ok(interfaces) = g.list_network_interfaces() {
                     result["network"] = serde_json::json!({
                         "interfaces": interfaces,
@@ -301,7 +198,8 @@ impl InspectHandler {
             }
 
             // Collect security information if requested
-            if payload_clone.options.include_security {
+            phase_context.check_cancelled()?;
+            if options.include_security {
                 let mut security = serde_json::json!({});
 
                 // SELinux status
@@ -443,8 +341,6 @@ impl OperationHandler for InspectHandler {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::progress::ProgressTracker;
-    use std::sync::Arc;
     use tempfile::TempDir;
 
     #[tokio::test]
@@ -3,8 +3,135 @@
 //! These handlers integrate with the guestkit core library to perform
 //! actual VM operations.
 
+pub mod compare;
+pub mod convert;
+pub mod fix;
 pub mod inspect;
 pub mod profile;
 
+pub use compare::CompareHandler;
+pub use convert::ConvertHandler;
+pub use fix::FixHandler;
 pub use inspect::InspectHandler;
 pub use profile::ProfileHandler;
+
+use crate::error::{WorkerError, WorkerResult};
+use crate::handler::HandlerContext;
+
+/// Resolve an image reference to a local path, verifying its checksum
+///
+/// `s3://` and `http(s)://` references are downloaded into the job's scratch
+/// directory, with the checksum checked while streaming. Local paths must
+/// exist and are hashed in place.
+pub(crate) async fn resolve_image(
+    context: &HandlerContext,
+    reference: &str,
+    checksum: Option<&str>,
+) -> WorkerResult<String> {
+    if crate::artifacts::is_remote(reference) {
+        let artifacts = context.artifacts.as_ref().ok_or_else(|| {
+            WorkerError::ExecutionError(format!(
+                "Remote image {} requires an artifact store", reference
+            ))
+        })?;
+
+        context.report_progress("fetch", None, format!("Fetching {}", reference)).await?;
+        let (path, bytes) = match artifacts.fetch(&context.job_id, reference, checksum).await {
+            Ok(fetched) => fetched,
+            Err(e @ WorkerError::ExecutionError(_)) if checksum.is_some() => {
+                context.record_checksum_verification("failure");
+                return Err(e);
+            }
+            Err(e) => return Err(e),
+        };
+        context.record_disk_write(bytes);
+        context.record_checksum_verification(if checksum.is_some() { "success" } else { "skipped" });
+        return Ok(path.to_string_lossy().to_string());
+    }
+
+    if !std::path::Path::new(reference).exists() {
+        return Err(WorkerError::ExecutionError(
+            format!("Image not found: {}", reference)
+        ));
+    }
+
+    if let Some(checksum) = checksum {
+        context.report_progress("validation", None, "Verifying image checksum").await?;
+        if !verify_checksum(reference, checksum).await? {
+            context.record_checksum_verification("failure");
+            return Err(WorkerError::ExecutionError(format!(
+                "Image checksum verification failed for {}. The image may be corrupted or tampered with.",
+                reference
+            )));
+        }
+        context.record_checksum_verification("success");
+    } else {
+        context.record_checksum_verification("skipped");
+    }
+
+    Ok(reference.to_string())
+}
+
+/// Verify image checksum if provided
+/// Supports format: "sha256:hexhash" or just "hexhash" (defaults to SHA256)
+pub(crate) async fn verify_checksum(path: &str, expected: &str) -> WorkerResult<bool> {
+    use sha2::{Sha256, Digest};
+    use std::io::Read;
+
+    log::info!("Verifying checksum for image: {}", path);
+
+    // Parse checksum format
+    let (algorithm, expected_hash) = if expected.contains(':') {
+        let parts: Vec<&str> = expected.splitn(2, ':').collect();
+        if parts.len() != 2 {
+            return Err(WorkerError::ExecutionError(
+                format!("Invalid checksum format: {}", expected)
+            ));
+        }
+        (parts[0].to_lowercase(), parts[1].to_lowercase())
+    } else {
+        // Default to SHA256 if no algorithm specified
+        ("sha256".to_string(), expected.to_lowercase())
+    };
+
+    // Only SHA256 is supported for now
+    if algorithm != "sha256" {
+        return Err(WorkerError::ExecutionError(
+            format!("Unsupported checksum algorithm: {}. Only 'sha256' is supported.", algorithm)
+        ));
+    }
+
+    // Open file and compute SHA256
+    let mut file = std::fs::File::open(path)
+        .map_err(|e| WorkerError::ExecutionError(
+            format!("Failed to open image for checksum verification: {}", e)
+        ))?;
+
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 8192]; // 8KB buffer for reading
+
+    loop {
+        let bytes_read = file.read(&mut buffer)
+            .map_err(|e| WorkerError::ExecutionError(
+                format!("Failed to read image during checksum verification: {}", e)
+            ))?;
+
+        if bytes_read == 0 {
+            break;
+        }
+
+        hasher.update(&buffer[..bytes_read]);
+    }
+
+    let computed_hash = format!("{:x}", hasher.finalize());
+
+    log::debug!("Checksum verification - Expected: {}, Computed: {}", expected_hash, computed_hash);
+
+    if computed_hash != expected_hash {
+        log::error!("Checksum mismatch! Expected: {}, Got: {}", expected_hash, computed_hash);
+        return Ok(false);
+    }
+
+    log::info!("Checksum verification successful");
+    Ok(true)
+}
@@ -97,6 +97,7 @@ impl ProfileHandler {
     ) -> WorkerResult<Vec<Finding>> {
         context.report_progress("security", Some(25), "Running security profile").await?;
 
+        let trace = context.trace.clone();
         let findings = tokio::task::spawn_blocking(move || -> WorkerResult<Vec<Finding>> {
             use guestkit::Guestfs;
 
@@ -106,11 +107,11 @@ impl ProfileHandler {
             g.add_drive_ro(&image_path)
                 .map_err(|e| WorkerError::ExecutionError(format!("Failed to add drive: {}", e)))?;
 
-            g.launch()
+            trace.in_span("guestfs.launch", || g.launch())
                 .map_err(|e| WorkerError::ExecutionError(format!("Failed to launch: {}", e)))?;
 
             // Inspect OS and mount root
-            let inspected = g.inspect()
+            let inspected = trace.in_span("guestfs.inspect", || g.inspect())
                 .map_err(|e| WorkerError::ExecutionError(format!("Failed to inspect: {}", e)))?;
 
             if inspected.is_empty() {
@@ -118,7 +119,7 @@ impl ProfileHandler {
             }
 
             let os_info = &inspected[0];
-            g.mount_ro(&os_info.root, "/")
+            trace.in_span("guestfs.mount", || g.mount_ro(&os_info.root, "/"))
                 .map_err(|e| WorkerError::ExecutionError(format!("Failed to mount: {}", e)))?;
 
             let mut findings = Vec::new();
@@ -193,6 +194,7 @@ impl ProfileHandler {
     ) -> WorkerResult<Vec<Finding>> {
         context.report_progress("compliance", Some(50), "Running compliance profile").await?;
 
+        let trace = context.trace.clone();
         let findings = tokio::task::spawn_blocking(move || -> WorkerResult<Vec<Finding>> {
             use guestkit::Guestfs;
 
@@ -202,10 +204,10 @@ impl ProfileHandler {
             g.add_drive_ro(&image_path)
                 .map_err(|e| WorkerError::ExecutionError(format!("Failed to add drive: {}", e)))?;
 
-            g.launch()
+            trace.in_span("guestfs.launch", || g.launch())
                 .map_err(|e| WorkerError::ExecutionError(format!("Failed to launch: {}", e)))?;
 
-            let inspected = g.inspect()
+            let inspected = trace.in_span("guestfs.inspect", || g.inspect())
                 .map_err(|e| WorkerError::ExecutionError(format!("Failed to inspect: {}", e)))?;
 
             if inspected.is_empty() {
@@ -213,7 +215,7 @@ impl ProfileHandler {
             }
 
             let os_info = &inspected[0];
-            g.mount_ro(&os_info.root, "/")
+            trace.in_span("guestfs.mount", || g.mount_ro(&os_info.root, "/"))
                 .map_err(|e| WorkerError::ExecutionError(format!("Failed to mount: {}", e)))?;
 
             let mut findings = Vec::new();
@@ -285,6 +287,7 @@ impl ProfileHandler {
     ) -> WorkerResult<Vec<Finding>> {
         context.report_progress("hardening", Some(75), "Running hardening profile").await?;
 
+        let trace = context.trace.clone();
         let findings = tokio::task::spawn_blocking(move || -> WorkerResult<Vec<Finding>> {
             use guestkit::Guestfs;
 
@@ -294,10 +297,10 @@ impl ProfileHandler {
             g.add_drive_ro(&image_path)
                 .map_err(|e| WorkerError::ExecutionError(format!("Failed to add drive: {}", e)))?;
 
-            g.launch()
+            trace.in_span("guestfs.launch", || g.launch())
                 .map_err(|e| WorkerError::ExecutionError(format!("Failed to launch: {}", e)))?;
 
-            let inspected = g.inspect()
+            let inspected = trace.in_span("guestfs.inspect", || g.inspect())
                 .map_err(|e| WorkerError::ExecutionError(format!("Failed to inspect: {}", e)))?;
 
             if inspected.is_empty() {
@@ -305,7 +308,7 @@ impl ProfileHandler {
             }
 
             let os_info = &inspected[0];
-            g.mount_ro(&os_info.root, "/")
+            trace.in_span("guestfs.mount", || g.mount_ro(&os_info.root, "/"))
                 .map_err(|e| WorkerError::ExecutionError(format!("Failed to mount: {}", e)))?;
 
             let mut findings = Vec::new();
@@ -461,7 +464,7 @@ impl OperationHandler for ProfileHandler {
 
         // Run requested profiles
         let mut all_findings = Vec::new();
-        let image_path = profile_payload.image.path.clone();
+        let image_path = super::resolve_image(&context, &profile_payload.image.path, None).await?;
 
         for profile_type in &profile_payload.profiles {
             let findings = match profile_type {
@@ -506,9 +509,6 @@ impl OperationHandler for ProfileHandler {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::progress::ProgressTracker;
-    use std::sync::Arc;
-    use tempfile::TempDir;
 
     #[tokio::test]
     async fn test_profile_handler() {
@@ -4,4 +4,19 @@ pub mod echo;
 pub mod guestkit;
 
 pub use echo::EchoHandler;
-pub use guestkit::{InspectHandler, ProfileHandler};
+pub use guestkit::{CompareHandler, ConvertHandler, FixHandler, InspectHandler, ProfileHandler};
+
+use std::sync::Arc;
+
+use crate::handler::HandlerRegistry;
+
+/// Register the built-in echo and guestkit operation handlers
+pub fn register_builtin(registry: &mut HandlerRegistry) {
+    registry.register(Arc::new(EchoHandler::new()));
+
+    registry.register(Arc::new(InspectHandler::new()));
+    registry.register(Arc::new(ConvertHandler::new()));
+    registry.register(Arc::new(FixHandler::new()));
+    registry.register(Arc::new(ProfileHandler::new()));
+    registry.register(Arc::new(CompareHandler::new()));
+}
@@ -0,0 +1,358 @@
+//! Job leases for workers sharing a job source
+//!
+//! Workers watching the same job directory each see every job. Before
+//! running one, a worker claims a lease on it in a shared lease directory.
+//! Each lease carries a fencing token and is created with a hard link, which
+//! fails if the file already exists, so only one worker can hold a given
+//! token. Leases expire unless renewed; a worker may take over an expired
+//! lease with the next token. Results are only written while the writer's
+//! token is still the newest one, so a worker that lost its lease cannot
+//! overwrite the result of the worker that took over.
+//!
+//! Layout: `<lease dir>/<job id>/<token>.json`, newest token wins.
+
+use chrono::{DateTime, Utc};
+use dashmap::{DashMap, DashSet};
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::time::Duration;
+use tokio::fs;
+use crate::error::{WorkerError, WorkerResult};
+
+/// Lease settings
+#[derive(Debug, Clone)]
+pub struct LeaseConfig {
+    /// Shared lease directory; leases are disabled when unset
+    pub dir: Option<PathBuf>,
+
+    /// Seconds a lease stays valid without renewal
+    pub ttl_secs: u64,
+}
+
+impl Default for LeaseConfig {
+    fn default() -> Self {
+        Self {
+            dir: None,
+            ttl_secs: 30,
+        }
+    }
+}
+
+/// Claim on a job by one worker
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Lease {
+    pub job_id: String,
+    pub worker_id: String,
+
+    /// Increases with every takeover of the job
+    pub token: u64,
+
+    pub expires_at: DateTime<Utc>,
+
+    /// Job finished; the lease never expires
+    #[serde(default)]
+    pub completed: bool,
+}
+
+impl Lease {
+    /// Whether the lease still keeps other workers off the job
+    pub fn is_active(&self, now: DateTime<Utc>) -> bool {
+        self.completed || self.expires_at > now
+    }
+}
+
+/// File-backed lease store shared by the workers of a job source
+pub struct LeaseStore {
+    dir: PathBuf,
+    worker_id: String,
+    ttl: Duration,
+
+    /// Leases this worker holds, by job
+    held: DashMap<String, Lease>,
+
+    /// Held leases found taken over by another worker
+    lost: DashSet<String>,
+}
+
+impl LeaseStore {
+    /// Open the lease directory for a worker
+    pub fn new(dir: impl Into<PathBuf>, worker_id: impl Into<String>, ttl_secs: u64) -> WorkerResult<Self> {
+        if ttl_secs == 0 {
+            return Err(WorkerError::InvalidConfig("lease TTL must be positive".to_string()));
+        }
+
+        let dir = dir.into();
+        std::fs::create_dir_all(&dir)?;
+
+        Ok(Self {
+            dir,
+            worker_id: worker_id.into(),
+            ttl: Duration::from_secs(ttl_secs),
+            held: DashMap::new(),
+            lost: DashSet::new(),
+        })
+    }
+
+    /// Lease validity without renewal
+    pub fn ttl(&self) -> Duration {
+        self.ttl
+    }
+
+    fn job_dir(&self, job_id: &str) -> PathBuf {
+        self.dir.join(job_id)
+    }
+
+    fn lease_path(&self, job_id: &str, token: u64) -> PathBuf {
+        self.job_dir(job_id).join(format!("{:020}.json", token))
+    }
+
+    fn expiry(&self) -> DateTime<Utc> {
+        Utc::now() + chrono::Duration::seconds(self.ttl.as_secs() as i64)
+    }
+
+    /// Tokens of the lease files for a job
+    async fn tokens(&self, job_id: &str) -> WorkerResult<Vec<u64>> {
+        let mut entries = match fs::read_dir(self.job_dir(job_id)).await {
+            Ok(entries) => entries,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut tokens = Vec::new();
+        while let Some(entry) = entries.next_entry().await? {
+            let path = entry.path();
+            if path.extension().and_then(|s| s.to_str()) != Some("json") {
+                continue;
+            }
+            if let Some(token) = path.file_stem().and_then(|s| s.to_str()).and_then(|s| s.parse().ok()) {
+                tokens.push(token);
+            }
+        }
+        Ok(tokens)
+    }
+
+    /// Newest lease on a job, if any
+    pub async fn current(&self, job_id: &str) -> WorkerResult<Option<Lease>> {
+        loop {
+            let Some(token) = self.tokens(job_id).await?.into_iter().max() else {
+                return Ok(None);
+            };
+            match fs::read(self.lease_path(job_id, token)).await {
+                Ok(contents) => return Ok(Some(serde_json::from_slice(&contents)?)),
+                // Pruned by a newer claim since the listing; look again
+                Err(e) if e.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(e) => return Err(e.into()),
+            }
+        }
+    }
+
+    /// Write a lease to a temporary file next to `path`
+    async fn write_file(&self, path: &Path, lease: &Lease) -> WorkerResult<PathBuf> {
+        let tmp = path.with_extension(format!("{}.tmp", self.worker_id));
+        fs::write(&tmp, serde_json::to_vec_pretty(lease)?).await?;
+        Ok(tmp)
+    }
+
+    /// Try to claim a job
+    ///
+    /// Returns `None` if another worker holds an active lease, the job was
+    /// completed, or another worker won the race for the same token.
+    pub async fn claim(&self, job_id: &str) -> WorkerResult<Option<Lease>> {
+        let now = Utc::now();
+        let token = match self.current(job_id).await? {
+            Some(current) if current.completed => return Ok(None),
+            Some(current) if current.is_active(now) && current.worker_id != self.worker_id => {
+                return Ok(None)
+            }
+            Some(current) => current.token + 1,
+            None => 1,
+        };
+
+        let lease = Lease {
+            job_id: job_id.to_string(),
+            worker_id: self.worker_id.clone(),
+            token,
+            expires_at: self.expiry(),
+            completed: false,
+        };
+
+        fs::create_dir_all(self.job_dir(job_id)).await?;
+        let path = self.lease_path(job_id, token);
+        let tmp = self.write_file(&path, &lease).await?;
+
+        // Linking fails if the token was taken in the meantime
+        let linked = fs::hard_link(&tmp, &path).await;
+        let _ = fs::remove_file(&tmp).await;
+        match linked {
+            Ok(()) => {}
+            Err(e) if e.kind() == std::io::ErrorKind::AlreadyExists => return Ok(None),
+            Err(e) => return Err(e.into()),
+        }
+
+        // Superseded lease files are no longer needed
+        for old in self.tokens(job_id).await? {
+            if old < token {
+                let _ = fs::remove_file(self.lease_path(job_id, old)).await;
+            }
+        }
+
+        self.lost.remove(job_id);
+        self.held.insert(job_id.to_string(), lease.clone());
+        Ok(Some(lease))
+    }
+
+    /// Whether a lease is still the newest one on its job
+    async fn is_current(&self, lease: &Lease) -> WorkerResult<bool> {
+        Ok(self
+            .current(&lease.job_id)
+            .await?
+            .is_some_and(|current| current.token == lease.token && current.worker_id == lease.worker_id))
+    }
+
+    /// Rewrite a held lease in place
+    async fn store(&self, lease: &Lease) -> WorkerResult<()> {
+        let path = self.lease_path(&lease.job_id, lease.token);
+        let tmp = self.write_file(&path, lease).await?;
+        fs::rename(&tmp, &path).await?;
+        Ok(())
+    }
+
+    /// Extend every held lease
+    ///
+    /// Returns the jobs whose lease was taken over by another worker; their
+    /// results will be refused and they should be cancelled.
+    pub async fn renew_all(&self) -> Vec<String> {
+        let leases: Vec<Lease> = self
+            .held
+            .iter()
+            .filter(|entry| !self.lost.contains(entry.key()))
+            .map(|entry| entry.value().clone())
+            .collect();
+
+        let mut lost = Vec::new();
+        for mut lease in leases {
+            match self.is_current(&lease).await {
+                Ok(true) => {
+                    lease.expires_at = self.expiry();
+                    match self.store(&lease).await {
+                        Ok(()) => {
+                            self.held.insert(lease.job_id.clone(), lease);
+                        }
+                        Err(e) => log::warn!("Failed to renew lease on job {}: {}", lease.job_id, e),
+                    }
+                }
+                Ok(false) => {
+                    self.lost.insert(lease.job_id.clone());
+                    lost.push(lease.job_id);
+                }
+                Err(e) => log::warn!("Failed to check lease on job {}: {}", lease.job_id, e),
+            }
+        }
+        lost
+    }
+
+    /// Fencing check before writing a job's result
+    ///
+    /// Jobs run without a lease pass unconditionally.
+    pub async fn check(&self, job_id: &str) -> WorkerResult<()> {
+        let Some(lease) = self.held.get(job_id).map(|entry| entry.value().clone()) else {
+            return Ok(());
+        };
+
+        if self.lost.contains(job_id) || !self.is_current(&lease).await? {
+            self.lost.insert(job_id.to_string());
+            return Err(WorkerError::LeaseLost {
+                job_id: job_id.to_string(),
+                token: lease.token,
+            });
+        }
+        Ok(())
+    }
+
+    /// Give up a held lease
+    ///
+    /// A completed lease keeps other workers from running the job again; an
+    /// abandoned one expires immediately so another worker can take over.
+    pub async fn release(&self, job_id: &str, completed: bool) {
+        self.lost.remove(job_id);
+        let Some((_, mut lease)) = self.held.remove(job_id) else {
+            return;
+        };
+
+        match self.is_current(&lease).await {
+            Ok(true) => {}
+            Ok(false) => return,
+            Err(e) => {
+                log::warn!("Failed to check lease on job {}: {}", job_id, e);
+                return;
+            }
+        }
+
+        lease.completed = completed;
+        if !completed {
+            lease.expires_at = Utc::now();
+        }
+        if let Err(e) = self.store(&lease).await {
+            log::warn!("Failed to release lease on job {}: {}", job_id, e);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[tokio::test]
+    async fn test_single_claim() {
+        let temp_dir = TempDir::new().unwrap();
+        let a = LeaseStore::new(temp_dir.path(), "worker-a", 30).unwrap();
+        let b = LeaseStore::new(temp_dir.path(), "worker-b", 30).unwrap();
+
+        let lease = a.claim("job-1").await.unwrap().unwrap();
+        assert_eq!(lease.token, 1);
+        assert!(b.claim("job-1").await.unwrap().is_none());
+
+        // Completed jobs are never claimed again
+        a.release("job-1", true).await;
+        assert!(b.claim("job-1").await.unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_takeover_fences_old_holder() {
+        let temp_dir = TempDir::new().unwrap();
+        let a = LeaseStore::new(temp_dir.path(), "worker-a", 1).unwrap();
+        let b = LeaseStore::new(temp_dir.path(), "worker-b", 30).unwrap();
+
+        a.claim("job-1").await.unwrap().unwrap();
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+
+        let lease = b.claim("job-1").await.unwrap().unwrap();
+        assert_eq!(lease.token, 2);
+
+        assert_eq!(a.renew_all().await, vec!["job-1".to_string()]);
+        assert!(matches!(
+            a.check("job-1").await,
+            Err(WorkerError::LeaseLost { token: 1, .. })
+        ));
+        assert!(b.check("job-1").await.is_ok());
+        assert_eq!(b.renew_all().await, Vec::<String>::new());
+
+        // The old holder's release leaves the new lease alone
+        a.release("job-1", true).await;
+        assert_eq!(b.current("job-1").await.unwrap().unwrap(), b.held.get("job-1").unwrap().clone());
+    }
+
+    #[tokio::test]
+    async fn test_abandoned_lease_can_be_taken_over() {
+        let temp_dir = TempDir::new().unwrap();
+        let a = LeaseStore::new(temp_dir.path(), "worker-a", 30).unwrap();
+        let b = LeaseStore::new(temp_dir.path(), "worker-b", 30).unwrap();
+
+        a.claim("job-1").await.unwrap().unwrap();
+        a.release("job-1", false).await;
+
+        assert_eq!(b.claim("job-1").await.unwrap().unwrap().token, 2);
+        assert!(a.check("job-1").await.is_ok());
+    }
+}
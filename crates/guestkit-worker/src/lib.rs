@@ -9,28 +9,53 @@ pub mod executor;
 pub mod handler;
 pub mod transport;
 pub mod state;
+pub mod store;
+pub mod lease;
 pub mod progress;
+pub mod events;
+pub mod cancel;
+pub mod concurrency;
+pub mod scheduler;
 pub mod result;
+pub mod artifacts;
+pub mod audit;
+pub mod discovery;
 pub mod handlers;
+pub mod plugins;
 pub mod metrics;
 pub mod metrics_server;
+pub mod telemetry;
 pub mod api;
 pub mod cli;
 
 // Re-exports
 pub use error::{WorkerError, WorkerResult};
 pub use worker::{Worker, WorkerConfig};
-pub use executor::JobExecutor;
+pub use executor::{JobExecutor, DependencyStatus};
 pub use handler::{OperationHandler, HandlerRegistry, HandlerContext};
 pub use transport::{JobTransport, FileTransport};
 pub use state::{JobState, JobStateMachine};
+pub use store::{JobRecord, JobStore, RecoveryAction};
+pub use lease::{Lease, LeaseConfig, LeaseStore};
 pub use progress::ProgressTracker;
+pub use events::{EventBus, JobEvent};
+pub use cancel::{CancelRegistry, CancelToken};
+pub use concurrency::{ConcurrencyClass, ConcurrencyLimiter, SlotPermit};
+pub use scheduler::{Scheduler, ScheduleEntry, ScheduledRun};
+pub use artifacts::{ArtifactConfig, ArtifactStore};
+pub use discovery::CapabilityProbe;
+pub use plugins::{NativePlugin, PluginManifest};
+pub use telemetry::{Span, TraceContext};
 
 /// Worker capabilities
 pub mod capabilities {
+    use guestkit_job_spec::Constraints;
     use serde::{Deserialize, Serialize};
+    use std::fmt;
 
     /// Worker capabilities set
+    ///
+    /// Resource limits of zero are unknown and not enforced.
     #[derive(Debug, Clone, Serialize, Deserialize, Default)]
     pub struct Capabilities {
         /// Supported operations
@@ -47,6 +72,37 @@ pub mod capabilities {
 
         /// Maximum disk size (GB)
         pub max_disk_size_gb: u64,
+
+        /// CPU cores
+        #[serde(default)]
+        pub cpu_cores: u32,
+
+        /// Memory (GB)
+        #[serde(default)]
+        pub memory_gb: u64,
+    }
+
+    /// A job constraint the worker does not satisfy
+    #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+    pub struct UnmetConstraint {
+        /// Constraint field (e.g., "constraints.min_memory_gb")
+        pub constraint: String,
+
+        /// What the job requires
+        pub required: serde_json::Value,
+
+        /// What the worker has
+        pub available: serde_json::Value,
+    }
+
+    impl fmt::Display for UnmetConstraint {
+        fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+            write!(
+                f,
+                "{} requires {}, worker has {}",
+                self.constraint, self.required, self.available
+            )
+        }
     }
 
     impl Capabilities {
@@ -73,6 +129,27 @@ pub mod capabilities {
             self
         }
 
+        /// Set the largest disk the worker accepts (GB)
+        pub fn with_max_disk_size_gb(mut self, size_gb: u64) -> Self {
+            self.max_disk_size_gb = size_gb;
+            self
+        }
+
+        /// Set CPU cores and memory (GB)
+        pub fn with_resources(mut self, cpu_cores: u32, memory_gb: u64) -> Self {
+            self.cpu_cores = cpu_cores;
+            self.memory_gb = memory_gb;
+            self
+        }
+
+        /// Set CPU cores and memory from the host
+        pub fn with_host_resources(self) -> Self {
+            let cpu_cores = std::thread::available_parallelism()
+                .map(|n| n.get() as u32)
+                .unwrap_or(0);
+            self.with_resources(cpu_cores, host_memory_gb().unwrap_or(0))
+        }
+
         /// Check if operation is supported
         pub fn supports_operation(&self, operation: &str) -> bool {
             self.operations.iter().any(|op| op == operation)
@@ -82,6 +159,64 @@ pub mod capabilities {
         pub fn has_feature(&self, feature: &str) -> bool {
             self.features.iter().any(|f| f == feature)
         }
+
+        /// Check a job's resource and feature constraints
+        ///
+        /// Returns the first constraint the worker does not meet.
+        pub fn check_constraints(&self, constraints: &Constraints) -> Result<(), UnmetConstraint> {
+            let unmet = |constraint: &str, required: serde_json::Value, available: serde_json::Value| {
+                Err(UnmetConstraint {
+                    constraint: format!("constraints.{}", constraint),
+                    required,
+                    available,
+                })
+            };
+
+            if let Some(ref features) = constraints.required_features {
+                let missing: Vec<String> = features
+                    .iter()
+                    .filter(|f| !self.has_feature(f))
+                    .cloned()
+                    .collect();
+                if !missing.is_empty() {
+                    return unmet("required_features", missing.into(), self.features.clone().into());
+                }
+            }
+
+            if let Some(size_gb) = constraints.maximum_disk_size_gb {
+                if self.max_disk_size_gb > 0 && size_gb > self.max_disk_size_gb {
+                    return unmet("maximum_disk_size_gb", size_gb.into(), self.max_disk_size_gb.into());
+                }
+            }
+
+            if let Some(cores) = constraints.min_cpu_cores {
+                if self.cpu_cores > 0 && cores > self.cpu_cores {
+                    return unmet("min_cpu_cores", cores.into(), self.cpu_cores.into());
+                }
+            }
+
+            if let Some(memory_gb) = constraints.min_memory_gb {
+                if self.memory_gb > 0 && memory_gb > self.memory_gb {
+                    return unmet("min_memory_gb", memory_gb.into(), self.memory_gb.into());
+                }
+            }
+
+            Ok(())
+        }
+    }
+
+    /// Total memory from /proc/meminfo, rounded down to whole GB
+    fn host_memory_gb() -> Option<u64> {
+        let meminfo = std::fs::read_to_string("/proc/meminfo").ok()?;
+        let kib: u64 = meminfo
+            .lines()
+            .find_map(|line| line.strip_prefix("MemTotal:"))?
+            .trim()
+            .trim_end_matches("kB")
+            .trim()
+            .parse()
+            .ok()?;
+        Some(kib / (1024 * 1024))
     }
 }
 
@@ -100,4 +235,49 @@ mod tests {
         assert!(caps.has_feature("lvm"));
         assert!(!caps.supports_operation("guestkit.fix"));
     }
+
+    #[test]
+    fn test_check_constraints() {
+        use guestkit_job_spec::Constraints;
+
+        let caps = capabilities::Capabilities::new()
+            .with_feature("lvm")
+            .with_max_disk_size_gb(500)
+            .with_resources(8, 16);
+
+        let fits = Constraints {
+            required_features: Some(vec!["lvm".to_string()]),
+            maximum_disk_size_gb: Some(500),
+            min_cpu_cores: Some(4),
+            min_memory_gb: Some(16),
+            ..Default::default()
+        };
+        assert!(caps.check_constraints(&fits).is_ok());
+
+        let too_much_memory = Constraints {
+            min_memory_gb: Some(32),
+            ..fits.clone()
+        };
+        let unmet = caps.check_constraints(&too_much_memory).unwrap_err();
+        assert_eq!(unmet.constraint, "constraints.min_memory_gb");
+        assert_eq!(unmet.required, 32);
+        assert_eq!(unmet.available, 16);
+
+        let missing_feature = Constraints {
+            required_features: Some(vec!["lvm".to_string(), "zfs".to_string()]),
+            ..Default::default()
+        };
+        let unmet = caps.check_constraints(&missing_feature).unwrap_err();
+        assert_eq!(unmet.constraint, "constraints.required_features");
+        assert_eq!(unmet.required, serde_json::json!(["zfs"]));
+
+        // Unknown limits are not enforced
+        assert!(capabilities::Capabilities::new()
+            .check_constraints(&Constraints {
+                maximum_disk_size_gb: Some(10_000),
+                min_cpu_cores: Some(64),
+                ..Default::default()
+            })
+            .is_ok());
+    }
 }
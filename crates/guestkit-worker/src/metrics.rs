@@ -21,6 +21,15 @@ pub struct JobLabels {
     pub status: String,
 }
 
+/// Labels for job queue metrics
+#[derive(Clone, Debug, Hash, PartialEq, Eq, prometheus_client::encoding::EncodeLabelSet)]
+pub struct QueueLabels {
+    /// Operation name (e.g., "guestkit.inspect")
+    pub operation: String,
+    /// Tenant (job namespace)
+    pub tenant: String,
+}
+
 /// Labels for handler metrics
 #[derive(Clone, Debug, Hash, PartialEq, Eq, prometheus_client::encoding::EncodeLabelSet)]
 pub struct HandlerLabels {
@@ -30,6 +39,22 @@ pub struct HandlerLabels {
     pub status: String,
 }
 
+/// Labels for handler error metrics
+#[derive(Clone, Debug, Hash, PartialEq, Eq, prometheus_client::encoding::EncodeLabelSet)]
+pub struct HandlerErrorLabels {
+    /// Handler name
+    pub handler: String,
+    /// Error kind (see `WorkerError::kind`)
+    pub kind: String,
+}
+
+/// Labels for in-flight job progress metrics
+#[derive(Clone, Debug, Hash, PartialEq, Eq, prometheus_client::encoding::EncodeLabelSet)]
+pub struct JobProgressLabels {
+    /// Operation name (e.g., "guestkit.convert")
+    pub operation: String,
+}
+
 /// Labels for checksum verification metrics
 #[derive(Clone, Debug, Hash, PartialEq, Eq, prometheus_client::encoding::EncodeLabelSet)]
 pub struct ChecksumLabels {
@@ -61,12 +86,16 @@ pub struct MetricsRegistry {
     pub active_jobs: Gauge,
     /// Queue depth (pending jobs)
     pub queue_depth: Gauge,
+    /// Time jobs waited for an execution slot
+    pub queue_wait_seconds: Family<QueueLabels, Histogram>,
 
     // Handler metrics
     /// Total handler executions
     pub handler_executions_total: Family<HandlerLabels, Counter>,
     /// Handler execution duration
     pub handler_duration_seconds: Family<HandlerLabels, Histogram>,
+    /// Handler failures by kind of error
+    pub handler_errors_total: Family<HandlerErrorLabels, Counter>,
 
     // Checksum verification metrics
     /// Checksum verification attempts
@@ -77,6 +106,8 @@ pub struct MetricsRegistry {
     pub disk_read_bytes_total: Counter,
     /// Disk bytes written
     pub disk_write_bytes_total: Counter,
+    /// Bytes processed so far by running jobs (e.g., image conversion)
+    pub bytes_processed: Family<JobProgressLabels, Gauge>,
 }
 
 impl MetricsRegistry {
@@ -84,10 +115,13 @@ impl MetricsRegistry {
     pub fn new() -> Self {
         let mut registry = Registry::default();
 
+        // Counters are registered without the "_total" suffix, which the
+        // encoder appends
+
         // Job metrics
         let jobs_total = Family::<JobLabels, Counter>::default();
         registry.register(
-            "guestkit_worker_jobs_total",
+            "guestkit_worker_jobs",
             "Total number of jobs processed",
             jobs_total.clone(),
         );
@@ -116,10 +150,20 @@ impl MetricsRegistry {
             queue_depth.clone(),
         );
 
+        let queue_wait_seconds = Family::<QueueLabels, Histogram>::new_with_constructor(|| {
+            // Buckets: 0.1s up to ~30m
+            Histogram::new(exponential_buckets(0.1, 2.0, 15))
+        });
+        registry.register(
+            "guestkit_worker_queue_wait_seconds",
+            "Time jobs waited for an execution slot",
+            queue_wait_seconds.clone(),
+        );
+
         // Handler metrics
         let handler_executions_total = Family::<HandlerLabels, Counter>::default();
         registry.register(
-            "guestkit_handler_executions_total",
+            "guestkit_handler_executions",
             "Total handler executions",
             handler_executions_total.clone(),
         );
@@ -133,10 +177,17 @@ impl MetricsRegistry {
             handler_duration_seconds.clone(),
         );
 
+        let handler_errors_total = Family::<HandlerErrorLabels, Counter>::default();
+        registry.register(
+            "guestkit_handler_errors",
+            "Handler failures by kind of error",
+            handler_errors_total.clone(),
+        );
+
         // Checksum verification metrics
         let checksum_verifications_total = Family::<ChecksumLabels, Counter>::default();
         registry.register(
-            "guestkit_checksum_verifications_total",
+            "guestkit_checksum_verifications",
             "Checksum verification attempts",
             checksum_verifications_total.clone(),
         );
@@ -144,29 +195,39 @@ impl MetricsRegistry {
         // Resource metrics
         let disk_read_bytes_total = Counter::default();
         registry.register(
-            "guestkit_worker_disk_read_bytes_total",
+            "guestkit_worker_disk_read_bytes",
             "Total disk bytes read",
             disk_read_bytes_total.clone(),
         );
 
         let disk_write_bytes_total = Counter::default();
         registry.register(
-            "guestkit_worker_disk_write_bytes_total",
+            "guestkit_worker_disk_write_bytes",
             "Total disk bytes written",
             disk_write_bytes_total.clone(),
         );
 
+        let bytes_processed = Family::<JobProgressLabels, Gauge>::default();
+        registry.register(
+            "guestkit_worker_bytes_processed",
+            "Bytes processed so far by running jobs, per operation",
+            bytes_processed.clone(),
+        );
+
         Self {
             registry: Arc::new(StdMutex::new(registry)),
             jobs_total,
             jobs_duration_seconds,
             active_jobs,
             queue_depth,
+            queue_wait_seconds,
             handler_executions_total,
             handler_duration_seconds,
+            handler_errors_total,
             checksum_verifications_total,
             disk_read_bytes_total,
             disk_write_bytes_total,
+            bytes_processed,
         }
     }
 
@@ -202,6 +263,25 @@ impl MetricsRegistry {
         self.handler_duration_seconds.get_or_create(&labels).observe(duration_seconds);
     }
 
+    /// Record a handler failure
+    pub fn record_handler_error(&self, handler: &str, kind: &str) {
+        let labels = HandlerErrorLabels {
+            handler: handler.to_string(),
+            kind: kind.to_string(),
+        };
+        self.handler_errors_total.get_or_create(&labels).inc();
+    }
+
+    /// Add to the bytes processed by running jobs of an operation
+    ///
+    /// A negative `bytes` takes a finished job's share back out.
+    pub fn add_bytes_processed(&self, operation: &str, bytes: i64) {
+        let labels = JobProgressLabels {
+            operation: operation.to_string(),
+        };
+        self.bytes_processed.get_or_create(&labels).inc_by(bytes);
+    }
+
     /// Record checksum verification
     pub fn record_checksum_verification(&self, status: &str) {
         let labels = ChecksumLabels {
@@ -225,6 +305,15 @@ impl MetricsRegistry {
         self.queue_depth.set(depth);
     }
 
+    /// Record how long a job waited for an execution slot
+    pub fn record_queue_wait(&self, operation: &str, tenant: &str, wait_seconds: f64) {
+        let labels = QueueLabels {
+            operation: operation.to_string(),
+            tenant: tenant.to_string(),
+        };
+        self.queue_wait_seconds.get_or_create(&labels).observe(wait_seconds);
+    }
+
     /// Record disk I/O
     pub fn record_disk_io(&self, read_bytes: u64, write_bytes: u64) {
         if read_bytes > 0 {
@@ -258,11 +347,14 @@ impl Clone for MetricsRegistry {
             jobs_duration_seconds: self.jobs_duration_seconds.clone(),
             active_jobs: self.active_jobs.clone(),
             queue_depth: self.queue_depth.clone(),
+            queue_wait_seconds: self.queue_wait_seconds.clone(),
             handler_executions_total: self.handler_executions_total.clone(),
             handler_duration_seconds: self.handler_duration_seconds.clone(),
+            handler_errors_total: self.handler_errors_total.clone(),
             checksum_verifications_total: self.checksum_verifications_total.clone(),
             disk_read_bytes_total: self.disk_read_bytes_total.clone(),
             disk_write_bytes_total: self.disk_write_bytes_total.clone(),
+            bytes_processed: self.bytes_processed.clone(),
         }
     }
 }
@@ -277,7 +369,7 @@ mod tests {
         let encoded = registry.encode();
 
         // Verify metrics are registered
-        assert!(encoded.contains("guestkit_worker_jobs_total"));
+        assert!(encoded.contains("# TYPE guestkit_worker_jobs counter"));
         assert!(encoded.contains("guestkit_worker_active_jobs"));
     }
 
@@ -334,6 +426,61 @@ mod tests {
         assert!(encoded.contains("profile"));
     }
 
+    #[test]
+    fn test_job_duration_histogram() {
+        let registry = MetricsRegistry::new();
+
+        registry.record_job_completion("guestkit.convert", "completed", 42.0);
+
+        let encoded = registry.encode();
+        assert!(encoded.contains(
+            r#"guestkit_worker_jobs_duration_seconds_bucket{le="64.0",operation="guestkit.convert",status="completed"} 1"#
+        ));
+        assert!(encoded.contains(
+            r#"guestkit_worker_jobs_duration_seconds_bucket{le="32.0",operation="guestkit.convert",status="completed"} 0"#
+        ));
+    }
+
+    #[test]
+    fn test_queue_wait_histogram() {
+        let registry = MetricsRegistry::new();
+
+        registry.record_queue_wait("guestkit.convert", "team-a", 3.0);
+
+        let encoded = registry.encode();
+        assert!(encoded.contains(
+            r#"guestkit_worker_queue_wait_seconds_count{operation="guestkit.convert",tenant="team-a"} 1"#
+        ));
+    }
+
+    #[test]
+    fn test_handler_error_metrics() {
+        let registry = MetricsRegistry::new();
+
+        registry.record_handler_error("convert", "timeout");
+        registry.record_handler_error("convert", "timeout");
+
+        let encoded = registry.encode();
+        assert!(encoded.contains(r#"guestkit_handler_errors_total{handler="convert",kind="timeout"} 2"#));
+    }
+
+    #[test]
+    fn test_bytes_processed_gauge() {
+        let registry = MetricsRegistry::new();
+
+        registry.add_bytes_processed("guestkit.convert", 1024);
+        registry.add_bytes_processed("guestkit.convert", 4096);
+        assert!(registry
+            .encode()
+            .contains(r#"guestkit_worker_bytes_processed{operation="guestkit.convert"} 5120"#));
+
+        // Finished jobs take their share back out
+        registry.add_bytes_processed("guestkit.convert", -1024);
+        assert!(registry
+            .encode()
+            .contains(r#"guestkit_worker_bytes_processed{operation="guestkit.convert"} 4096"#));
+    }
+
     #[test]
     fn test_disk_io_metrics() {
         let registry = MetricsRegistry::new();
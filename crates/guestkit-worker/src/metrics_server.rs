@@ -64,6 +64,9 @@ impl MetricsServer {
     }
 }
 
+/// Content type of the OpenMetrics text format produced by the encoder
+const OPENMETRICS_CONTENT_TYPE: &str = "application/openmetrics-text; version=1.0.0; charset=utf-8";
+
 /// Handler for /metrics endpoint
 async fn metrics_handler(
     State(metrics): State<Arc<MetricsRegistry>>,
@@ -71,7 +74,7 @@ async fn metrics_handler(
     let body = metrics.encode();
     (
         StatusCode::OK,
-        [("content-type", "text/plain; version=0.0.4")],
+        [("content-type", OPENMETRICS_CONTENT_TYPE)],
         body,
     )
         .into_response()
@@ -108,6 +111,28 @@ mod tests {
         assert_eq!(response.status(), StatusCode::OK);
     }
 
+    #[tokio::test]
+    async fn test_metrics_handler_exports_labels() {
+        let metrics = Arc::new(MetricsRegistry::new());
+        metrics.record_job_completion("guestkit.convert", "completed", 90.0);
+        metrics.record_handler_error("guestkit-convert", "io");
+        metrics.add_bytes_processed("guestkit.convert", 2048);
+        metrics.set_queue_depth(3);
+
+        let response = metrics_handler(State(metrics)).await;
+        assert_eq!(
+            response.headers()["content-type"],
+            OPENMETRICS_CONTENT_TYPE
+        );
+
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        let body = String::from_utf8(body.to_vec()).unwrap();
+        assert!(body.contains(r#"guestkit_worker_jobs_duration_seconds_count{operation="guestkit.convert",status="completed"} 1"#));
+        assert!(body.contains(r#"guestkit_handler_errors_total{handler="guestkit-convert",kind="io"} 1"#));
+        assert!(body.contains(r#"guestkit_worker_bytes_processed{operation="guestkit.convert"} 2048"#));
+        assert!(body.contains("guestkit_worker_queue_depth 3"));
+    }
+
     #[tokio::test]
     async fn test_health_handler() {
         let response = health_handler().await;
@@ -0,0 +1,385 @@
+//! Handler plugins loaded at startup
+//!
+//! Operators add job operations without rebuilding the worker by dropping
+//! shared libraries (`cdylib` crates, or any language with a C ABI) into the
+//! plugin directory. Each library exports four functions:
+//!
+//! ```c
+//! uint32_t    guestkit_worker_plugin_abi_version(void);  /* PLUGIN_ABI_VERSION */
+//! const char *guestkit_worker_plugin_manifest(void);     /* static JSON PluginManifest */
+//! char       *guestkit_worker_plugin_execute(const char *request); /* JSON in, JSON out */
+//! void        guestkit_worker_plugin_free(char *response);
+//! ```
+//!
+//! The request is a JSON [`PluginRequest`], the response a JSON
+//! [`PluginResponse`] the worker hands back to `guestkit_worker_plugin_free`.
+//! `execute` runs on a blocking thread and may be called for several jobs at
+//! once, so it must be thread-safe. It must not unwind across the boundary.
+//!
+//! Libraries are loaded with `dlopen` and run inside the worker process with
+//! its privileges; only install plugins you would build into the worker.
+
+use async_trait::async_trait;
+use guestkit_job_spec::Payload;
+use serde::{Deserialize, Serialize};
+use std::ffi::{c_char, CStr, CString};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use crate::error::{WorkerError, WorkerResult};
+use crate::handler::{HandlerContext, HandlerRegistry, HandlerResult, OperationHandler};
+
+/// Version of the plugin ABI, raised on incompatible changes
+pub const PLUGIN_ABI_VERSION: u32 = 1;
+
+const ABI_VERSION_SYMBOL: &[u8] = b"guestkit_worker_plugin_abi_version\0";
+const MANIFEST_SYMBOL: &[u8] = b"guestkit_worker_plugin_manifest\0";
+const EXECUTE_SYMBOL: &[u8] = b"guestkit_worker_plugin_execute\0";
+const FREE_SYMBOL: &[u8] = b"guestkit_worker_plugin_free\0";
+
+type AbiVersionFn = unsafe extern "C" fn() -> u32;
+type ManifestFn = unsafe extern "C" fn() -> *const c_char;
+type ExecuteFn = unsafe extern "C" fn(*const c_char) -> *mut c_char;
+type FreeFn = unsafe extern "C" fn(*mut c_char);
+
+/// What a plugin declares about itself
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PluginManifest {
+    /// Handler name, for logs
+    pub name: String,
+
+    /// Plugin version
+    #[serde(default)]
+    pub version: Option<String>,
+
+    /// Operations the plugin handles (e.g., "acme.scan")
+    pub operations: Vec<String>,
+
+    /// Features the worker advertises while the plugin is loaded
+    #[serde(default)]
+    pub features: Vec<String>,
+}
+
+/// Request passed to `guestkit_worker_plugin_execute`
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginRequest {
+    pub abi_version: u32,
+    pub operation: String,
+    pub job_id: String,
+    pub worker_id: String,
+    pub work_dir: PathBuf,
+    pub payload: Payload,
+}
+
+/// Response of `guestkit_worker_plugin_execute`
+///
+/// A response with `error` set fails the job with that message.
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(default)]
+pub struct PluginResponse {
+    pub output_file: Option<String>,
+    pub artifacts: Vec<String>,
+    pub data: serde_json::Value,
+    pub error: Option<String>,
+}
+
+impl PluginResponse {
+    /// Parse a response, failing on `error`
+    pub fn parse(json: &str) -> WorkerResult<HandlerResult> {
+        let response: PluginResponse = serde_json::from_str(json)
+            .map_err(|e| WorkerError::PluginError(format!("Invalid response: {}", e)))?;
+        if let Some(error) = response.error {
+            return Err(WorkerError::ExecutionError(error));
+        }
+
+        let mut result = HandlerResult::new().with_data(response.data);
+        result.output_file = response.output_file;
+        result.artifacts = response.artifacts;
+        Ok(result)
+    }
+}
+
+/// Entry points of a loaded library, valid as long as the library is
+struct Entry {
+    execute: ExecuteFn,
+    free: FreeFn,
+    _library: libloading::Library,
+}
+
+impl Entry {
+    fn call(&self, request: &CStr) -> WorkerResult<String> {
+        // SAFETY: the plugin implements the documented ABI, which was
+        // version-checked at load time
+        unsafe {
+            let response = (self.execute)(request.as_ptr());
+            if response.is_null() {
+                return Err(WorkerError::PluginError("No response".to_string()));
+            }
+            let json = CStr::from_ptr(response).to_string_lossy().into_owned();
+            (self.free)(response);
+            Ok(json)
+        }
+    }
+}
+
+/// A handler backed by a plugin library
+pub struct NativePlugin {
+    path: PathBuf,
+    manifest: PluginManifest,
+    entry: Arc<Entry>,
+}
+
+impl NativePlugin {
+    /// Load the plugin library at `path`
+    pub fn load(path: impl AsRef<Path>) -> WorkerResult<Self> {
+        let path = path.as_ref();
+        let error = |message: String| {
+            WorkerError::PluginError(format!("{}: {}", path.display(), message))
+        };
+
+        // SAFETY: loading runs the library's initializers; plugins are
+        // trusted code installed by the operator
+        unsafe {
+            let library = libloading::Library::new(path).map_err(|e| error(e.to_string()))?;
+
+            let abi_version: AbiVersionFn = *library
+                .get::<AbiVersionFn>(ABI_VERSION_SYMBOL)
+                .map_err(|_| error("not a guestkit-worker plugin".to_string()))?;
+            let version = abi_version();
+            if version != PLUGIN_ABI_VERSION {
+                return Err(error(format!(
+                    "built for plugin ABI {}, this worker supports {}",
+                    version, PLUGIN_ABI_VERSION
+                )));
+            }
+
+            let manifest: ManifestFn = *library
+                .get::<ManifestFn>(MANIFEST_SYMBOL)
+                .map_err(|e| error(e.to_string()))?;
+            let execute: ExecuteFn = *library
+                .get::<ExecuteFn>(EXECUTE_SYMBOL)
+                .map_err(|e| error(e.to_string()))?;
+            let free: FreeFn = *library
+                .get::<FreeFn>(FREE_SYMBOL)
+                .map_err(|e| error(e.to_string()))?;
+
+            let manifest = manifest();
+            if manifest.is_null() {
+                return Err(error("no manifest".to_string()));
+            }
+            let manifest = parse_manifest(&CStr::from_ptr(manifest).to_string_lossy())
+                .map_err(error)?;
+
+            Ok(Self {
+                path: path.to_path_buf(),
+                manifest,
+                entry: Arc::new(Entry {
+                    execute,
+                    free,
+                    _library: library,
+                }),
+            })
+        }
+    }
+
+    /// The plugin's manifest
+    pub fn manifest(&self) -> &PluginManifest {
+        &self.manifest
+    }
+
+    /// The library the plugin was loaded from
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Parse and check a manifest
+fn parse_manifest(json: &str) -> Result<PluginManifest, String> {
+    let manifest: PluginManifest =
+        serde_json::from_str(json).map_err(|e| format!("invalid manifest: {}", e))?;
+    if manifest.name.is_empty() {
+        return Err("manifest has no name".to_string());
+    }
+    if manifest.operations.is_empty() {
+        return Err("manifest declares no operations".to_string());
+    }
+    if let Some(operation) = manifest.operations.iter().find(|op| !op.contains('.')) {
+        return Err(format!(
+            "operation '{}' is not namespaced (e.g., 'acme.scan')",
+            operation
+        ));
+    }
+    Ok(manifest)
+}
+
+#[async_trait]
+impl OperationHandler for NativePlugin {
+    fn name(&self) -> &str {
+        &self.manifest.name
+    }
+
+    fn operations(&self) -> Vec<String> {
+        self.manifest.operations.clone()
+    }
+
+    async fn execute(
+        &self,
+        context: HandlerContext,
+        payload: Payload,
+    ) -> WorkerResult<HandlerResult> {
+        context.checkpoint().await?;
+        context
+            .report_progress("running", None, format!("Running plugin {}", self.manifest.name))
+            .await?;
+
+        let request = PluginRequest {
+            abi_version: PLUGIN_ABI_VERSION,
+            operation: payload.payload_type.clone(),
+            job_id: context.job_id.clone(),
+            worker_id: context.worker_id.clone(),
+            work_dir: context.work_dir.clone(),
+            payload,
+        };
+        // JSON escapes NUL, so the request never contains one
+        let request = CString::new(serde_json::to_vec(&request)?)
+            .map_err(|e| WorkerError::PluginError(e.to_string()))?;
+
+        // A running plugin cannot be interrupted; cancellation takes effect
+        // when it returns
+        let entry = Arc::clone(&self.entry);
+        let response = tokio::task::spawn_blocking(move || entry.call(&request))
+            .await
+            .map_err(|e| WorkerError::ExecutionError(format!("Plugin task failed: {}", e)))??;
+        context.checkpoint().await?;
+
+        PluginResponse::parse(&response).map_err(|e| match e {
+            WorkerError::PluginError(message) => WorkerError::PluginError(format!(
+                "{}: {}",
+                self.path.display(),
+                message
+            )),
+            e => e,
+        })
+    }
+}
+
+/// Load the plugins in `dir` and register them
+///
+/// Libraries that fail to load are skipped with an error in the log, as are
+/// plugins claiming an operation that is already registered: built-in
+/// handlers and earlier plugins (in file name order) take precedence.
+/// Returns the manifests of the registered plugins.
+pub fn load_plugins(
+    dir: impl AsRef<Path>,
+    registry: &mut HandlerRegistry,
+) -> WorkerResult<Vec<PluginManifest>> {
+    let dir = dir.as_ref();
+    let mut paths: Vec<PathBuf> = std::fs::read_dir(dir)
+        .map_err(|e| {
+            WorkerError::InvalidConfig(format!(
+                "Cannot read plugin directory {}: {}",
+                dir.display(),
+                e
+            ))
+        })?
+        .filter_map(|entry| entry.ok().map(|entry| entry.path()))
+        .filter(|path| {
+            path.is_file()
+                && path.extension().and_then(|ext| ext.to_str())
+                    == Some(std::env::consts::DLL_EXTENSION)
+        })
+        .collect();
+    paths.sort();
+
+    let mut loaded = Vec::new();
+    for path in paths {
+        let plugin = match NativePlugin::load(&path) {
+            Ok(plugin) => plugin,
+            Err(e) => {
+                log::error!("Skipping plugin: {}", e);
+                continue;
+            }
+        };
+
+        if let Some(operation) = plugin
+            .manifest
+            .operations
+            .iter()
+            .find(|operation| registry.supports(operation))
+        {
+            log::error!(
+                "Skipping plugin {}: operation '{}' is already handled",
+                path.display(),
+                operation
+            );
+            continue;
+        }
+
+        log::info!(
+            "Loaded plugin '{}' {} from {}",
+            plugin.manifest.name,
+            plugin.manifest.version.as_deref().unwrap_or(""),
+            path.display()
+        );
+        loaded.push(plugin.manifest.clone());
+        registry.register(Arc::new(plugin));
+    }
+    Ok(loaded)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    #[test]
+    fn test_parse_manifest() {
+        let manifest = parse_manifest(
+            r#"{"name": "acme-scanner", "version": "1.2.0",
+                "operations": ["acme.scan"], "features": ["acme"]}"#,
+        )
+        .unwrap();
+        assert_eq!(manifest.operations, vec!["acme.scan"]);
+        assert_eq!(manifest.features, vec!["acme"]);
+
+        assert!(parse_manifest(r#"{"name": "acme", "operations": []}"#).is_err());
+        assert!(parse_manifest(r#"{"name": "acme", "operations": ["scan"]}"#).is_err());
+        assert!(parse_manifest("not json").is_err());
+    }
+
+    #[test]
+    fn test_parse_response() {
+        let result = PluginResponse::parse(
+            r#"{"output_file": "/tmp/report.json", "data": {"findings": 3}}"#,
+        )
+        .unwrap();
+        assert_eq!(result.output_file.as_deref(), Some("/tmp/report.json"));
+        assert_eq!(result.data["findings"], 3);
+
+        let error = PluginResponse::parse(r#"{"error": "image has no root filesystem"}"#)
+            .unwrap_err();
+        assert!(matches!(error, WorkerError::ExecutionError(message)
+            if message == "image has no root filesystem"));
+        assert!(matches!(
+            PluginResponse::parse("{").unwrap_err(),
+            WorkerError::PluginError(_)
+        ));
+    }
+
+    #[test]
+    fn test_load_plugins_skips_invalid_libraries() {
+        let dir = TempDir::new().unwrap();
+        let library = dir
+            .path()
+            .join(format!("libbroken.{}", std::env::consts::DLL_EXTENSION));
+        std::fs::write(&library, b"not a shared library").unwrap();
+        std::fs::write(dir.path().join("README"), b"").unwrap();
+
+        let mut registry = HandlerRegistry::new();
+        let loaded = load_plugins(dir.path(), &mut registry).unwrap();
+        assert!(loaded.is_empty());
+        assert!(registry.is_empty());
+
+        assert!(NativePlugin::load(&library).is_err());
+        assert!(load_plugins(dir.path().join("missing"), &mut registry).is_err());
+    }
+}
@@ -1,16 +1,22 @@
 //! Job result persistence
 
 use guestkit_job_spec::{
-    JobResultType, JobStatus, ExecutionSummary, JobOutputs, JobExecutionError,
+    JobResultType, JobStatus, ExecutionSummary, ExecutionMetrics, JobOutputs, JobExecutionError,
 };
 use chrono::Utc;
 use std::path::Path;
+use std::sync::Arc;
 use tokio::fs;
+use crate::capabilities::UnmetConstraint;
 use crate::error::WorkerResult;
+use crate::lease::LeaseStore;
 
 /// Result writer
 pub struct ResultWriter {
     output_dir: std::path::PathBuf,
+
+    /// Leases fencing off results of jobs taken over by another worker
+    leases: Option<Arc<LeaseStore>>,
 }
 
 impl ResultWriter {
@@ -18,9 +24,16 @@ impl ResultWriter {
     pub fn new(output_dir: impl Into<std::path::PathBuf>) -> Self {
         Self {
             output_dir: output_dir.into(),
+            leases: None,
         }
     }
 
+    /// Refuse results for jobs whose lease this worker no longer holds
+    pub fn with_leases(mut self, leases: Arc<LeaseStore>) -> Self {
+        self.leases = Some(leases);
+        self
+    }
+
     /// Write successful result
     pub async fn write_success(
         &self,
@@ -31,6 +44,7 @@ impl ResultWriter {
         idempotency_key: Option<String>,
         output_file: Option<String>,
         artifacts: Vec<String>,
+        metrics: Option<ExecutionMetrics>,
     ) -> WorkerResult<String> {
         let duration = (Utc::now() - started_at).num_seconds() as u64;
 
@@ -54,7 +68,7 @@ impl ResultWriter {
                     Some(artifacts)
                 },
             }),
-            metrics: None,
+            metrics,
             error: None,
             observability: None,
         };
@@ -73,6 +87,7 @@ impl ResultWriter {
         error_message: impl Into<String>,
         phase: Option<String>,
         recoverable: bool,
+        metrics: Option<ExecutionMetrics>,
     ) -> WorkerResult<String> {
         let duration = (Utc::now() - started_at).num_seconds() as u64;
 
@@ -89,7 +104,7 @@ impl ResultWriter {
                 idempotency_key: None,
             },
             outputs: None,
-            metrics: None,
+            metrics,
             error: Some(JobExecutionError {
                 code: error_code.into(),
                 message: error_message.into(),
@@ -104,8 +119,103 @@ impl ResultWriter {
         self.write_result(&result).await
     }
 
+    /// Write rejection result for a job whose constraints this worker does not meet
+    ///
+    /// The unmet constraint is recorded in the error details so a scheduler
+    /// can route the job to a worker that fits.
+    pub async fn write_rejection(
+        &self,
+        job_id: &str,
+        worker_id: &str,
+        started_at: chrono::DateTime<Utc>,
+        attempt: u32,
+        unmet: &UnmetConstraint,
+    ) -> WorkerResult<String> {
+        let duration = (Utc::now() - started_at).num_seconds() as u64;
+
+        let details = [
+            ("constraint".to_string(), unmet.constraint.clone().into()),
+            ("required".to_string(), unmet.required.clone()),
+            ("available".to_string(), unmet.available.clone()),
+        ]
+        .into_iter()
+        .collect();
+
+        let result = JobResultType {
+            job_id: job_id.to_string(),
+            status: JobStatus::Failed,
+            completed_at: None,
+            failed_at: Some(Utc::now()),
+            worker_id: worker_id.to_string(),
+            execution_summary: ExecutionSummary {
+                started_at,
+                duration_seconds: duration,
+                attempt,
+                idempotency_key: None,
+            },
+            outputs: None,
+            metrics: None,
+            error: Some(JobExecutionError {
+                code: "CONSTRAINT_NOT_MET".to_string(),
+                message: format!("Worker does not meet job constraints: {}", unmet),
+                phase: Some("validation".to_string()),
+                details: Some(details),
+                // Another worker may fit; retrying here will not help
+                recoverable: true,
+                retry_recommended: false,
+            }),
+            observability: None,
+        };
+
+        self.write_result(&result).await
+    }
+
+    /// Write cancellation result, keeping metrics gathered before the stop
+    pub async fn write_cancelled(
+        &self,
+        job_id: &str,
+        worker_id: &str,
+        started_at: chrono::DateTime<Utc>,
+        attempt: u32,
+        reason: impl Into<String>,
+        metrics: Option<ExecutionMetrics>,
+    ) -> WorkerResult<String> {
+        let duration = (Utc::now() - started_at).num_seconds() as u64;
+
+        let result = JobResultType {
+            job_id: job_id.to_string(),
+            status: JobStatus::Cancelled,
+            completed_at: None,
+            failed_at: Some(Utc::now()),
+            worker_id: worker_id.to_string(),
+            execution_summary: ExecutionSummary {
+                started_at,
+                duration_seconds: duration,
+                attempt,
+                idempotency_key: None,
+            },
+            outputs: None,
+            metrics,
+            error: Some(JobExecutionError {
+                code: "CANCELLED".to_string(),
+                message: reason.into(),
+                phase: Some("execution".to_string()),
+                details: None,
+                recoverable: true,
+                retry_recommended: false,
+            }),
+            observability: None,
+        };
+
+        self.write_result(&result).await
+    }
+
     /// Write result to file
     async fn write_result(&self, result: &JobResultType) -> WorkerResult<String> {
+        if let Some(ref leases) = self.leases {
+            leases.check(&result.job_id).await?;
+        }
+
         fs::create_dir_all(&self.output_dir).await?;
 
         let filename = format!("{}-result.json", result.job_id);
@@ -160,6 +270,7 @@ mod tests {
                 Some("idempotency-key".to_string()),
                 Some("/output/result.json".to_string()),
                 vec!["/output/log.txt".to_string()],
+                None,
             )
             .await
             .unwrap();
@@ -189,6 +300,7 @@ mod tests {
                 "Job validation failed",
                 Some("validation".to_string()),
                 false,
+                None,
             )
             .await
             .unwrap();
@@ -200,4 +312,32 @@ mod tests {
         assert_eq!(result.status, JobStatus::Failed);
         assert!(result.error.is_some());
     }
+
+    #[tokio::test]
+    async fn test_write_cancelled_result() {
+        let temp_dir = TempDir::new().unwrap();
+        let writer = ResultWriter::new(temp_dir.path());
+
+        let metrics = ExecutionMetrics {
+            disk_read_bytes: Some(4096),
+            ..Default::default()
+        };
+
+        writer
+            .write_cancelled(
+                "job-test-789",
+                "worker-01",
+                Utc::now(),
+                1,
+                "Cancelled by user",
+                Some(metrics),
+            )
+            .await
+            .unwrap();
+
+        let result = writer.read_result("job-test-789").await.unwrap();
+        assert_eq!(result.status, JobStatus::Cancelled);
+        assert_eq!(result.error.unwrap().code, "CANCELLED");
+        assert_eq!(result.metrics.unwrap().disk_read_bytes, Some(4096));
+    }
 }
@@ -0,0 +1,851 @@
+//! Scheduled and recurring jobs
+//!
+//! Jobs whose execution policy carries a `schedule` are not executed
+//! directly. They are persisted as templates and a fresh `JobDocument` is
+//! instantiated from the template each time the schedule triggers.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+use std::str::FromStr;
+use std::sync::Arc;
+use chrono::{DateTime, Datelike, Duration, NaiveDateTime, Timelike, Utc};
+use guestkit_job_spec::{JobDocument, JobSchedule, OverlapPolicy};
+use serde::{Deserialize, Serialize};
+use tokio::fs;
+use crate::error::{WorkerError, WorkerResult};
+use crate::result::ResultWriter;
+
+/// Label added to every job instantiated from a schedule
+pub const SCHEDULE_LABEL: &str = "guestkit.schedule_id";
+
+/// Upper bound on cron candidates examined when filtering by RRULE interval
+const MAX_CANDIDATES: usize = 100_000;
+
+/// RRULE frequency
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Frequency {
+    Minutely,
+    Hourly,
+    Daily,
+    Weekly,
+    Monthly,
+}
+
+/// Parsed recurrence rule
+///
+/// Cron expressions map directly onto a `cron::Schedule`. RRULEs are
+/// translated to a cron schedule anchored at `DTSTART` (or the schedule's
+/// creation time), with `INTERVAL`, `COUNT` and `UNTIL` applied on top.
+#[derive(Debug, Clone)]
+pub struct Recurrence {
+    schedule: cron::Schedule,
+    anchor: DateTime<Utc>,
+    frequency: Option<Frequency>,
+    interval: u32,
+    count: Option<u32>,
+    until: Option<DateTime<Utc>>,
+}
+
+impl Recurrence {
+    /// Parse a cron expression or RRULE
+    pub fn parse(expression: &str, anchor: DateTime<Utc>) -> WorkerResult<Self> {
+        let expression = expression.trim();
+
+        if expression.contains("FREQ=") {
+            return Self::parse_rrule(expression, anchor);
+        }
+
+        let normalized = normalize_cron(expression)?;
+        let schedule = cron::Schedule::from_str(&normalized).map_err(|e| {
+            WorkerError::InvalidConfig(format!("invalid cron expression '{}': {}", expression, e))
+        })?;
+
+        Ok(Self {
+            schedule,
+            anchor,
+            frequency: None,
+            interval: 1,
+            count: None,
+            until: None,
+        })
+    }
+
+    fn parse_rrule(expression: &str, anchor: DateTime<Utc>) -> WorkerResult<Self> {
+        let invalid = |reason: String| {
+            WorkerError::InvalidConfig(format!("invalid RRULE '{}': {}", expression, reason))
+        };
+
+        let mut anchor = anchor;
+        let mut frequency = None;
+        let mut interval = 1;
+        let mut count = None;
+        let mut until = None;
+        let mut by_minute = None;
+        let mut by_hour = None;
+        let mut by_day = None;
+        let mut by_month_day = None;
+
+        for part in expression.split(|c: char| c == ';' || c.is_whitespace()) {
+            let part = part.strip_prefix("RRULE:").unwrap_or(part);
+            if part.is_empty() {
+                continue;
+            }
+
+            let (key, value) = part
+                .split_once(['=', ':'])
+                .ok_or_else(|| invalid(format!("malformed part '{}'", part)))?;
+
+            match key {
+                "FREQ" => {
+                    frequency = Some(match value {
+                        "MINUTELY" => Frequency::Minutely,
+                        "HOURLY" => Frequency::Hourly,
+                        "DAILY" => Frequency::Daily,
+                        "WEEKLY" => Frequency::Weekly,
+                        "MONTHLY" => Frequency::Monthly,
+                        other => return Err(invalid(format!("unsupported FREQ {}", other))),
+                    })
+                }
+                "INTERVAL" => {
+                    interval = value
+                        .parse()
+                        .ok()
+                        .filter(|i| *i > 0)
+                        .ok_or_else(|| invalid(format!("bad INTERVAL {}", value)))?
+                }
+                "COUNT" => {
+                    count = Some(
+                        value
+                            .parse()
+                            .map_err(|_| invalid(format!("bad COUNT {}", value)))?,
+                    )
+                }
+                "UNTIL" | "DTSTART" => {
+                    let time = parse_rrule_time(value)
+                        .ok_or_else(|| invalid(format!("bad {} {}", key, value)))?;
+                    if key == "UNTIL" {
+                        until = Some(time);
+                    } else {
+                        anchor = time;
+                    }
+                }
+                "BYMINUTE" | "BYHOUR" | "BYMONTHDAY" => {
+                    let list = numeric_list(value)
+                        .ok_or_else(|| invalid(format!("bad {} {}", key, value)))?;
+                    match key {
+                        "BYMINUTE" => by_minute = Some(list),
+                        "BYHOUR" => by_hour = Some(list),
+                        _ => by_month_day = Some(list),
+                    }
+                }
+                "BYDAY" => {
+                    by_day = Some(
+                        weekday_list(value).ok_or_else(|| invalid(format!("bad BYDAY {}", value)))?,
+                    )
+                }
+                "WKST" => {}
+                other => return Err(invalid(format!("unsupported part {}", other))),
+            }
+        }
+
+        let frequency = frequency.ok_or_else(|| invalid("missing FREQ".to_string()))?;
+
+        // Fields not constrained by a BY* part default to the anchor's value
+        let minute = match frequency {
+            Frequency::Minutely => "*".to_string(),
+            _ => by_minute.unwrap_or_else(|| anchor.minute().to_string()),
+        };
+        let hour = match frequency {
+            Frequency::Minutely | Frequency::Hourly => by_hour.unwrap_or_else(|| "*".to_string()),
+            _ => by_hour.unwrap_or_else(|| anchor.hour().to_string()),
+        };
+        let month_day = match frequency {
+            Frequency::Monthly if by_day.is_none() => {
+                by_month_day.unwrap_or_else(|| anchor.day().to_string())
+            }
+            _ => by_month_day.unwrap_or_else(|| "*".to_string()),
+        };
+        let weekday = match frequency {
+            Frequency::Weekly => {
+                by_day.unwrap_or_else(|| cron_weekday(anchor.weekday()).to_string())
+            }
+            _ => by_day.unwrap_or_else(|| "*".to_string()),
+        };
+
+        let cron_expr = format!(
+            "{} {} {} {} * {}",
+            anchor.second(),
+            minute,
+            hour,
+            month_day,
+            weekday
+        );
+        let schedule = cron::Schedule::from_str(&cron_expr).map_err(|e| invalid(e.to_string()))?;
+
+        Ok(Self {
+            schedule,
+            anchor,
+            frequency: Some(frequency),
+            interval,
+            count,
+            until,
+        })
+    }
+
+    /// Maximum number of runs, if the rule is bounded by `COUNT`
+    pub fn max_runs(&self) -> Option<u32> {
+        self.count
+    }
+
+    /// Next occurrence strictly after `after`
+    pub fn next_after(&self, after: DateTime<Utc>) -> Option<DateTime<Utc>> {
+        let start = if self.frequency.is_some() && after < self.anchor {
+            self.anchor - Duration::seconds(1)
+        } else {
+            after
+        };
+
+        for occurrence in self.schedule.after(&start).take(MAX_CANDIDATES) {
+            if self.until.is_some_and(|until| occurrence > until) {
+                return None;
+            }
+            if self.in_interval(occurrence) {
+                return Some(occurrence);
+            }
+        }
+
+        None
+    }
+
+    /// Check whether an occurrence falls in a period selected by `INTERVAL`
+    fn in_interval(&self, occurrence: DateTime<Utc>) -> bool {
+        let Some(frequency) = self.frequency else {
+            return true;
+        };
+        if self.interval <= 1 {
+            return true;
+        }
+
+        let anchor = self.anchor;
+        let period = match frequency {
+            Frequency::Minutely => occurrence.timestamp() / 60 - anchor.timestamp() / 60,
+            Frequency::Hourly => occurrence.timestamp() / 3600 - anchor.timestamp() / 3600,
+            Frequency::Daily => (occurrence.date_naive() - anchor.date_naive()).num_days(),
+            Frequency::Weekly => {
+                let week_start = |dt: DateTime<Utc>| {
+                    dt.date_naive() - Duration::days(i64::from(dt.weekday().num_days_from_monday()))
+                };
+                (week_start(occurrence) - week_start(anchor)).num_days() / 7
+            }
+            Frequency::Monthly => {
+                let months = |dt: DateTime<Utc>| i64::from(dt.year()) * 12 + i64::from(dt.month0());
+                months(occurrence) - months(anchor)
+            }
+        };
+
+        period >= 0 && period % i64::from(self.interval) == 0
+    }
+}
+
+/// Convert a standard 5-field crontab expression to the 6-field form
+///
+/// The cron crate expects a leading seconds field and numbers weekdays
+/// 1 (Sunday) to 7 (Saturday), where crontab uses 0 or 7 for Sunday.
+fn normalize_cron(expression: &str) -> WorkerResult<String> {
+    if expression.starts_with('@') {
+        return Ok(expression.to_string());
+    }
+
+    let fields: Vec<&str> = expression.split_whitespace().collect();
+    match fields.len() {
+        5 => {
+            let weekday = fields[4]
+                .split(',')
+                .map(shift_weekday_item)
+                .collect::<Vec<_>>()
+                .join(",");
+            Ok(format!("0 {} {} {} {} {}", fields[0], fields[1], fields[2], fields[3], weekday))
+        }
+        6 | 7 => Ok(fields.join(" ")),
+        n => Err(WorkerError::InvalidConfig(format!(
+            "cron expression '{}' has {} fields, expected 5-7",
+            expression, n
+        ))),
+    }
+}
+
+/// Shift one crontab weekday list item (`1`, `1-5`, `*/2`) to cron-crate numbering
+fn shift_weekday_item(item: &str) -> String {
+    let (base, step) = match item.split_once('/') {
+        Some((base, step)) => (base, Some(step)),
+        None => (item, None),
+    };
+
+    let shift = |value: &str| match value.parse::<u32>() {
+        Ok(n) if n <= 7 => ((n % 7) + 1).to_string(),
+        _ => value.to_string(),
+    };
+
+    let base = match base.split_once('-') {
+        // A range ending on Sunday (e.g. `5-7`) wraps in cron-crate numbering
+        Some((start, "7")) if step.is_none() && start != "0" => format!("{}-7,1", shift(start)),
+        Some((start, end)) => format!("{}-{}", shift(start), shift(end)),
+        None => shift(base),
+    };
+
+    match step {
+        Some(step) => format!("{}/{}", base, step),
+        None => base,
+    }
+}
+
+fn parse_rrule_time(value: &str) -> Option<DateTime<Utc>> {
+    let value = value.trim_end_matches('Z');
+    NaiveDateTime::parse_from_str(value, "%Y%m%dT%H%M%S")
+        .ok()
+        .map(|dt| dt.and_utc())
+}
+
+fn numeric_list(value: &str) -> Option<String> {
+    let valid = value
+        .split(',')
+        .all(|n| !n.is_empty() && n.chars().all(|c| c.is_ascii_digit()));
+    valid.then(|| value.to_string())
+}
+
+fn weekday_list(value: &str) -> Option<String> {
+    value
+        .split(',')
+        .map(|day| {
+            Some(match day {
+                "MO" => "Mon",
+                "TU" => "Tue",
+                "WE" => "Wed",
+                "TH" => "Thu",
+                "FR" => "Fri",
+                "SA" => "Sat",
+                "SU" => "Sun",
+                _ => return None,
+            })
+        })
+        .collect::<Option<Vec<_>>>()
+        .map(|days| days.join(","))
+}
+
+fn cron_weekday(weekday: chrono::Weekday) -> &'static str {
+    match weekday {
+        chrono::Weekday::Mon => "Mon",
+        chrono::Weekday::Tue => "Tue",
+        chrono::Weekday::Wed => "Wed",
+        chrono::Weekday::Thu => "Thu",
+        chrono::Weekday::Fri => "Fri",
+        chrono::Weekday::Sat => "Sat",
+        chrono::Weekday::Sun => "Sun",
+    }
+}
+
+/// Random delay of up to `max_seconds`
+fn jitter(max_seconds: u64) -> Duration {
+    if max_seconds == 0 {
+        return Duration::zero();
+    }
+    let seconds = uuid::Uuid::new_v4().as_u128() % (u128::from(max_seconds) + 1);
+    Duration::seconds(seconds as i64)
+}
+
+/// Persisted schedule entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScheduleEntry {
+    /// Schedule ID (the template's job ID)
+    pub schedule_id: String,
+
+    /// Job template instantiated at each trigger
+    pub template: JobDocument,
+
+    /// When the schedule was registered
+    pub created_at: DateTime<Utc>,
+
+    /// Next trigger time, including jitter (None once exhausted)
+    pub next_run: Option<DateTime<Utc>>,
+
+    /// Last trigger time
+    pub last_run: Option<DateTime<Utc>>,
+
+    /// Number of jobs instantiated so far
+    pub run_count: u32,
+
+    /// Job ID of the most recent run
+    pub last_job_id: Option<String>,
+
+    /// Most recent run, while it has not produced a result
+    ///
+    /// Not persisted: a run cannot outlive the worker that started it.
+    #[serde(skip)]
+    pub active_job: Option<String>,
+}
+
+impl ScheduleEntry {
+    fn schedule(&self) -> WorkerResult<&JobSchedule> {
+        self.template
+            .execution
+            .as_ref()
+            .and_then(|e| e.schedule.as_ref())
+            .ok_or_else(|| {
+                WorkerError::InvalidConfig(format!("job {} has no schedule", self.schedule_id))
+            })
+    }
+
+    /// Compute the next trigger time after `after`
+    fn compute_next_run(&self, after: DateTime<Utc>) -> WorkerResult<Option<DateTime<Utc>>> {
+        let schedule = self.schedule()?;
+        let recurrence = Recurrence::parse(&schedule.expression, self.created_at)?;
+
+        if recurrence.max_runs().is_some_and(|max| self.run_count >= max) {
+            return Ok(None);
+        }
+
+        Ok(recurrence
+            .next_after(after)
+            .map(|next| next + jitter(schedule.jitter_seconds)))
+    }
+
+    /// Create a job for one run of this schedule
+    fn instantiate(&self, now: DateTime<Utc>) -> JobDocument {
+        let mut job = self.template.clone();
+
+        job.job_id = format!(
+            "{}-{}",
+            self.schedule_id,
+            ulid::Ulid::new().to_string().to_lowercase()
+        );
+        job.created_at = now;
+
+        if let Some(ref mut execution) = job.execution {
+            execution.schedule = None;
+            // Keep runs distinct for idempotency checks
+            if let Some(ref key) = execution.idempotency_key {
+                execution.idempotency_key = Some(format!("{}-{}", key, self.run_count + 1));
+            }
+        }
+
+        job.metadata
+            .get_or_insert_with(Default::default)
+            .labels
+            .get_or_insert_with(HashMap::new)
+            .insert(SCHEDULE_LABEL.to_string(), self.schedule_id.clone());
+
+        job
+    }
+}
+
+/// Reject schedule IDs that cannot be used as a file name in the schedule
+/// directory
+fn check_schedule_id(schedule_id: &str) -> WorkerResult<()> {
+    if schedule_id.is_empty()
+        || schedule_id.contains(['/', '\\'])
+        || schedule_id.contains("..")
+    {
+        return Err(WorkerError::InvalidConfig(format!(
+            "invalid schedule ID: {:?}",
+            schedule_id
+        )));
+    }
+    Ok(())
+}
+
+/// Job instantiated by a schedule trigger
+#[derive(Debug, Clone)]
+pub struct ScheduledRun {
+    /// Schedule that triggered
+    pub schedule_id: String,
+
+    /// Job to execute
+    pub job: JobDocument,
+
+    /// Previous run to cancel first (overlap policy `replace`)
+    pub replaces: Option<String>,
+}
+
+/// Scheduler for recurring jobs
+pub struct Scheduler {
+    schedule_dir: PathBuf,
+    result_writer: Arc<ResultWriter>,
+    entries: HashMap<String, ScheduleEntry>,
+}
+
+impl Scheduler {
+    /// Load persisted schedules from a directory
+    pub async fn load(
+        schedule_dir: impl Into<PathBuf>,
+        result_writer: Arc<ResultWriter>,
+    ) -> WorkerResult<Self> {
+        let schedule_dir = schedule_dir.into();
+        fs::create_dir_all(&schedule_dir).await?;
+
+        let mut entries = HashMap::new();
+        let mut dir = fs::read_dir(&schedule_dir).await?;
+        while let Some(file) = dir.next_entry().await? {
+            let path = file.path();
+            if path.extension().and_then(|e| e.to_str()) != Some("json") {
+                continue;
+            }
+
+            let json = fs::read_to_string(&path).await?;
+            match serde_json::from_str::<ScheduleEntry>(&json) {
+                Ok(entry) if check_schedule_id(&entry.schedule_id).is_err() => {
+                    log::warn!("Ignoring schedule {} with an invalid ID", path.display());
+                }
+                Ok(entry) => {
+                    entries.insert(entry.schedule_id.clone(), entry);
+                }
+                Err(e) => log::warn!("Ignoring unreadable schedule {}: {}", path.display(), e),
+            }
+        }
+
+        log::info!("Loaded {} schedule(s) from {}", entries.len(), schedule_dir.display());
+
+        Ok(Self {
+            schedule_dir,
+            result_writer,
+            entries,
+        })
+    }
+
+    /// Register a scheduled job template
+    ///
+    /// Re-submitting a template with the same job ID replaces the schedule
+    /// but keeps its run history and RRULE anchor.
+    pub async fn add(&mut self, template: JobDocument) -> WorkerResult<&ScheduleEntry> {
+        let now = Utc::now();
+        let schedule_id = template.job_id.clone();
+        check_schedule_id(&schedule_id)?;
+
+        let mut entry = match self.entries.remove(&schedule_id) {
+            Some(existing) => ScheduleEntry {
+                template,
+                ..existing
+            },
+            None => ScheduleEntry {
+                schedule_id: schedule_id.clone(),
+                template,
+                created_at: now,
+                next_run: None,
+                last_run: None,
+                run_count: 0,
+                last_job_id: None,
+                active_job: None,
+            },
+        };
+        entry.next_run = entry.compute_next_run(now)?;
+
+        log::info!(
+            "Registered schedule {} ({}), next run {:?}",
+            schedule_id,
+            entry.schedule()?.expression,
+            entry.next_run
+        );
+
+        self.persist(&entry).await?;
+        Ok(self.entries.entry(schedule_id).or_insert(entry))
+    }
+
+    /// Remove a schedule
+    pub async fn remove(&mut self, schedule_id: &str) -> WorkerResult<bool> {
+        if self.entries.remove(schedule_id).is_none() {
+            return Ok(false);
+        }
+
+        let path = self.entry_path(schedule_id);
+        if path.exists() {
+            fs::remove_file(&path).await?;
+        }
+
+        Ok(true)
+    }
+
+    /// Get a schedule
+    pub fn get(&self, schedule_id: &str) -> Option<&ScheduleEntry> {
+        self.entries.get(schedule_id)
+    }
+
+    /// Iterate over all schedules
+    pub fn entries(&self) -> impl Iterator<Item = &ScheduleEntry> {
+        self.entries.values()
+    }
+
+    /// Instantiate jobs for every schedule due at `now`
+    ///
+    /// Missed triggers (e.g. while the worker was down) collapse into a
+    /// single run; the next trigger is always computed after `now`.
+    pub async fn due(&mut self, now: DateTime<Utc>) -> WorkerResult<Vec<ScheduledRun>> {
+        let mut due: Vec<String> = self
+            .entries
+            .values()
+            .filter(|entry| entry.next_run.is_some_and(|next| next <= now))
+            .map(|entry| entry.schedule_id.clone())
+            .collect();
+        due.sort();
+
+        let mut runs = Vec::new();
+
+        for schedule_id in due {
+            let Some(entry) = self.entries.get_mut(&schedule_id) else {
+                continue;
+            };
+
+            if let Some(ref active) = entry.active_job {
+                if self.result_writer.result_exists(active).await {
+                    entry.active_job = None;
+                }
+            }
+
+            let overlap = entry.schedule()?.overlap;
+            let replaces = match (overlap, entry.active_job.clone()) {
+                (OverlapPolicy::Forbid, Some(active)) => {
+                    log::info!(
+                        "Skipping run of schedule {}: previous run {} still active",
+                        schedule_id,
+                        active
+                    );
+                    entry.next_run = entry.compute_next_run(now)?;
+                    let entry = entry.clone();
+                    self.persist(&entry).await?;
+                    continue;
+                }
+                (OverlapPolicy::Replace, active) => active,
+                _ => None,
+            };
+
+            let job = entry.instantiate(now);
+            entry.run_count += 1;
+            entry.last_run = Some(now);
+            entry.last_job_id = Some(job.job_id.clone());
+            entry.active_job = Some(job.job_id.clone());
+            entry.next_run = entry.compute_next_run(now)?;
+
+            log::info!(
+                "Schedule {} triggered job {} (run {}), next run {:?}",
+                schedule_id,
+                job.job_id,
+                entry.run_count,
+                entry.next_run
+            );
+
+            let entry = entry.clone();
+            self.persist(&entry).await?;
+
+            runs.push(ScheduledRun {
+                schedule_id,
+                job,
+                replaces,
+            });
+        }
+
+        Ok(runs)
+    }
+
+    fn entry_path(&self, schedule_id: &str) -> PathBuf {
+        self.schedule_dir.join(format!("{}.json", schedule_id))
+    }
+
+    async fn persist(&self, entry: &ScheduleEntry) -> WorkerResult<()> {
+        let json = serde_json::to_string_pretty(entry)?;
+        fs::write(self.entry_path(&entry.schedule_id), json).await?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+    use guestkit_job_spec::builder::JobBuilder;
+    use tempfile::TempDir;
+
+    fn at(y: i32, m: u32, d: u32, h: u32, min: u32) -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(y, m, d, h, min, 0).unwrap()
+    }
+
+    fn scheduled_job(expression: &str, overlap: OverlapPolicy) -> JobDocument {
+        JobBuilder::new()
+            .job_id("nightly-inspect")
+            .operation("test.operation")
+            .payload("test.operation.v1", serde_json::json!({}))
+            .schedule(expression)
+            .schedule_policy(0, overlap)
+            .build()
+            .unwrap()
+    }
+
+    #[test]
+    fn test_crontab_normalization() {
+        assert_eq!(normalize_cron("30 2 * * *").unwrap(), "0 30 2 * * *");
+        assert_eq!(normalize_cron("0 9 * * 1-5").unwrap(), "0 0 9 * * 2-6");
+        assert_eq!(normalize_cron("0 9 * * 0,6").unwrap(), "0 0 9 * * 1,7");
+        assert_eq!(normalize_cron("0 9 * * 5-7").unwrap(), "0 0 9 * * 6-7,1");
+        assert_eq!(normalize_cron("0 9 * * */2").unwrap(), "0 0 9 * * */2");
+        assert_eq!(normalize_cron("@daily").unwrap(), "@daily");
+        assert!(normalize_cron("* *").is_err());
+    }
+
+    #[test]
+    fn test_cron_next_run() {
+        let anchor = at(2024, 1, 1, 0, 0);
+
+        // Monday 2024-01-01; weekdays at 09:00
+        let recurrence = Recurrence::parse("0 9 * * 1-5", anchor).unwrap();
+        assert_eq!(recurrence.next_after(anchor), Some(at(2024, 1, 1, 9, 0)));
+        assert_eq!(recurrence.next_after(at(2024, 1, 5, 10, 0)), Some(at(2024, 1, 8, 9, 0)));
+
+        // Sunday as 0
+        let recurrence = Recurrence::parse("15 3 * * 0", anchor).unwrap();
+        assert_eq!(recurrence.next_after(anchor), Some(at(2024, 1, 7, 3, 15)));
+    }
+
+    #[test]
+    fn test_rrule_next_run() {
+        let anchor = at(2024, 1, 1, 0, 0);
+
+        let recurrence =
+            Recurrence::parse("RRULE:FREQ=DAILY;INTERVAL=2;BYHOUR=2;BYMINUTE=30", anchor).unwrap();
+        assert_eq!(recurrence.next_after(anchor), Some(at(2024, 1, 1, 2, 30)));
+        assert_eq!(recurrence.next_after(at(2024, 1, 1, 3, 0)), Some(at(2024, 1, 3, 2, 30)));
+
+        let recurrence = Recurrence::parse(
+            "DTSTART:20240102T060000Z\nRRULE:FREQ=WEEKLY;BYDAY=MO,FR;UNTIL=20240112T000000Z",
+            anchor,
+        )
+        .unwrap();
+        assert_eq!(recurrence.next_after(anchor), Some(at(2024, 1, 5, 6, 0)));
+        assert_eq!(recurrence.next_after(at(2024, 1, 5, 6, 0)), Some(at(2024, 1, 8, 6, 0)));
+        assert_eq!(recurrence.next_after(at(2024, 1, 8, 6, 0)), None);
+
+        assert!(Recurrence::parse("RRULE:FREQ=YEARLY", anchor).is_err());
+        assert!(Recurrence::parse("RRULE:FREQ=MONTHLY;BYDAY=1MO", anchor).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_triggers_and_persists() {
+        let temp_dir = TempDir::new().unwrap();
+        let writer = Arc::new(ResultWriter::new(temp_dir.path().join("results")));
+        let schedule_dir = temp_dir.path().join("schedules");
+
+        let mut scheduler = Scheduler::load(&schedule_dir, writer.clone()).await.unwrap();
+        let next_run = scheduler
+            .add(scheduled_job("* * * * *", OverlapPolicy::Allow))
+            .await
+            .unwrap()
+            .next_run
+            .unwrap();
+
+        assert!(scheduler.due(next_run - Duration::seconds(1)).await.unwrap().is_empty());
+
+        let runs = scheduler.due(next_run).await.unwrap();
+        assert_eq!(runs.len(), 1);
+        let job = &runs[0].job;
+        assert!(job.job_id.starts_with("nightly-inspect-"));
+        assert!(job.execution.as_ref().unwrap().schedule.is_none());
+        assert_eq!(
+            job.metadata.as_ref().unwrap().labels.as_ref().unwrap()[SCHEDULE_LABEL],
+            "nightly-inspect"
+        );
+
+        // Reload from disk
+        let scheduler = Scheduler::load(&schedule_dir, writer).await.unwrap();
+        let entry = scheduler.get("nightly-inspect").unwrap();
+        assert_eq!(entry.run_count, 1);
+        assert_eq!(entry.last_job_id.as_deref(), Some(job.job_id.as_str()));
+        assert!(entry.next_run.unwrap() > next_run);
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_overlap_policies() {
+        let temp_dir = TempDir::new().unwrap();
+        let writer = Arc::new(ResultWriter::new(temp_dir.path().join("results")));
+
+        for (overlap, expect_run) in [
+            (OverlapPolicy::Forbid, false),
+            (OverlapPolicy::Allow, true),
+            (OverlapPolicy::Replace, true),
+        ] {
+            let schedule_dir = temp_dir.path().join(format!("{:?}", overlap));
+            let mut scheduler = Scheduler::load(schedule_dir, writer.clone()).await.unwrap();
+            scheduler.add(scheduled_job("* * * * *", overlap)).await.unwrap();
+
+            let now = Utc::now() + Duration::minutes(1);
+            let first = scheduler.due(now).await.unwrap();
+            assert_eq!(first.len(), 1);
+
+            // First run has no result yet, so it is still active
+            let second = scheduler.due(now + Duration::minutes(1)).await.unwrap();
+            assert_eq!(second.len(), usize::from(expect_run), "{:?}", overlap);
+
+            if overlap == OverlapPolicy::Replace {
+                assert_eq!(second[0].replaces.as_deref(), Some(first[0].job.job_id.as_str()));
+            } else if expect_run {
+                assert!(second[0].replaces.is_none());
+            }
+        }
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_rrule_count() {
+        let temp_dir = TempDir::new().unwrap();
+        let writer = Arc::new(ResultWriter::new(temp_dir.path().join("results")));
+        let mut scheduler = Scheduler::load(temp_dir.path(), writer).await.unwrap();
+
+        scheduler
+            .add(scheduled_job("RRULE:FREQ=MINUTELY;COUNT=2", OverlapPolicy::Allow))
+            .await
+            .unwrap();
+
+        let mut now = Utc::now();
+        let mut total = 0;
+        for _ in 0..4 {
+            now += Duration::minutes(1);
+            total += scheduler.due(now).await.unwrap().len();
+        }
+
+        assert_eq!(total, 2);
+        assert!(scheduler.get("nightly-inspect").unwrap().next_run.is_none());
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_readd_keeps_anchor() {
+        let temp_dir = TempDir::new().unwrap();
+        let writer = Arc::new(ResultWriter::new(temp_dir.path().join("results")));
+        let mut scheduler = Scheduler::load(temp_dir.path(), writer).await.unwrap();
+
+        let created_at = scheduler
+            .add(scheduled_job("RRULE:FREQ=HOURLY", OverlapPolicy::Allow))
+            .await
+            .unwrap()
+            .created_at;
+
+        tokio::time::sleep(std::time::Duration::from_millis(10)).await;
+        let entry = scheduler
+            .add(scheduled_job("RRULE:FREQ=HOURLY;INTERVAL=2", OverlapPolicy::Allow))
+            .await
+            .unwrap();
+
+        assert_eq!(entry.created_at, created_at);
+        assert!(entry.schedule().unwrap().expression.contains("INTERVAL=2"));
+    }
+
+    #[tokio::test]
+    async fn test_scheduler_rejects_path_ids() {
+        let temp_dir = TempDir::new().unwrap();
+        let writer = Arc::new(ResultWriter::new(temp_dir.path().join("results")));
+        let mut scheduler = Scheduler::load(temp_dir.path().join("schedules"), writer)
+            .await
+            .unwrap();
+
+        for id in ["../escape-0001", "nested/sched-0001"] {
+            let mut job = scheduled_job("* * * * *", OverlapPolicy::Allow);
+            job.job_id = id.to_string();
+            assert!(scheduler.add(job).await.is_err());
+        }
+        assert!(!temp_dir.path().join("escape-0001.json").exists());
+        assert_eq!(scheduler.entries().count(), 0);
+    }
+}
@@ -19,6 +19,9 @@ pub enum JobState {
     /// Job currently executing
     Running,
 
+    /// Job stopped at a checkpoint until resumed
+    Paused,
+
     /// Job completed successfully
     Completed,
 
@@ -39,6 +42,7 @@ impl std::fmt::Display for JobState {
             JobState::Queued => write!(f, "queued"),
             JobState::Assigned => write!(f, "assigned"),
             JobState::Running => write!(f, "running"),
+            JobState::Paused => write!(f, "paused"),
             JobState::Completed => write!(f, "completed"),
             JobState::Failed => write!(f, "failed"),
             JobState::Cancelled => write!(f, "cancelled"),
@@ -58,7 +62,7 @@ impl JobState {
 
     /// Check if state is active (job is being processed)
     pub fn is_active(&self) -> bool {
-        matches!(self, JobState::Running)
+        matches!(self, JobState::Running | JobState::Paused)
     }
 }
 
@@ -135,6 +139,14 @@ impl JobStateMachine {
             (Running, Failed) => true,
             (Running, Cancelled) => true,
             (Running, Timeout) => true,
+            (Running, Paused) => true,
+
+            // From Paused; handlers that skip checkpoints may finish while paused
+            (Paused, Running) => true,
+            (Paused, Completed) => true,
+            (Paused, Failed) => true,
+            (Paused, Cancelled) => true,
+            (Paused, Timeout) => true,
 
             // Invalid transitions
             _ => false,
@@ -190,4 +202,19 @@ mod tests {
         // Can cancel from Assigned
         assert!(sm.transition(JobState::Cancelled).is_ok());
     }
+
+    #[test]
+    fn test_pause_resume() {
+        let mut sm = JobStateMachine::from_state(JobState::Running);
+
+        assert!(sm.transition(JobState::Paused).is_ok());
+        assert!(sm.current().is_active());
+        assert!(sm.transition(JobState::Running).is_ok());
+        assert!(sm.transition(JobState::Paused).is_ok());
+        assert!(sm.transition(JobState::Cancelled).is_ok());
+
+        // Only running jobs can be paused
+        let mut sm = JobStateMachine::from_state(JobState::Queued);
+        assert!(sm.transition(JobState::Paused).is_err());
+    }
 }
@@ -0,0 +1,272 @@
+//! Persistent job state store
+//!
+//! Every accepted job and each of its state transitions is recorded in an
+//! embedded sled database under the worker's state directory. A record is
+//! removed once the job's result document has been written, so whatever is
+//! left at startup was in flight when the previous process died.
+
+use chrono::{DateTime, Utc};
+use guestkit_job_spec::JobDocument;
+use serde::{Deserialize, Deserializer, Serialize};
+use std::path::Path;
+use crate::error::WorkerResult;
+use crate::state::JobState;
+
+/// Job with its last recorded state
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JobRecord {
+    /// Records written by an older worker may hold an older protocol version
+    #[serde(deserialize_with = "deserialize_any_version")]
+    pub job: JobDocument,
+    pub state: JobState,
+    pub worker_id: String,
+    pub updated_at: DateTime<Utc>,
+}
+
+fn deserialize_any_version<'de, D: Deserializer<'de>>(deserializer: D) -> Result<JobDocument, D::Error> {
+    let value = serde_json::Value::deserialize(deserializer)?;
+    JobDocument::from_value_any(value).map_err(serde::de::Error::custom)
+}
+
+/// What to do with a job found unfinished at startup
+#[derive(Debug, Clone)]
+pub enum RecoveryAction {
+    /// Job never started running; run it again as is
+    Requeue(JobDocument),
+
+    /// Job was running and has attempts left; attempt number incremented
+    Retry(JobDocument),
+
+    /// Job was running with no attempts left
+    Fail(JobRecord),
+
+    /// Result was written before the crash; only the record is stale
+    Discard(String),
+}
+
+impl RecoveryAction {
+    /// Decide how to recover a record left by a previous run
+    ///
+    /// Jobs that were still waiting can simply run again. A job that was
+    /// running may have half-modified its image, so it is only retried when
+    /// its execution policy allows another attempt.
+    pub fn for_record(record: JobRecord, has_result: bool) -> Self {
+        if has_result || record.state.is_terminal() {
+            return RecoveryAction::Discard(record.job.job_id);
+        }
+
+        if !record.state.is_active() {
+            return RecoveryAction::Requeue(record.job);
+        }
+
+        let (attempt, max_attempts) = record
+            .job
+            .execution
+            .as_ref()
+            .map(|e| (e.attempt, e.max_attempts))
+            .unwrap_or((1, 1));
+
+        if attempt < max_attempts {
+            let mut job = record.job;
+            if let Some(ref mut execution) = job.execution {
+                execution.attempt += 1;
+            }
+            RecoveryAction::Retry(job)
+        } else {
+            RecoveryAction::Fail(record)
+        }
+    }
+}
+
+/// Durable store of unfinished jobs
+pub struct JobStore {
+    db: sled::Db,
+}
+
+impl JobStore {
+    /// Open (or create) the store at `path`
+    pub fn open(path: &Path) -> WorkerResult<Self> {
+        let db = sled::open(path)?;
+        Ok(Self { db })
+    }
+
+    /// Store that lives only as long as the process (for tests)
+    pub fn temporary() -> WorkerResult<Self> {
+        let db = sled::Config::new().temporary(true).open()?;
+        Ok(Self { db })
+    }
+
+    fn put(&self, record: &JobRecord) -> WorkerResult<()> {
+        let value = serde_json::to_vec(record)?;
+        self.db.insert(record.job.job_id.as_bytes(), value)?;
+        // Records only matter if they survive a crash
+        self.db.flush()?;
+        Ok(())
+    }
+
+    /// Record a job (replacing any earlier record for the same id)
+    pub fn insert(&self, job: &JobDocument, state: JobState, worker_id: &str) -> WorkerResult<()> {
+        self.put(&JobRecord {
+            job: job.clone(),
+            state,
+            worker_id: worker_id.to_string(),
+            updated_at: Utc::now(),
+        })
+    }
+
+    /// Update a recorded job's state; unknown jobs are ignored
+    pub fn set_state(&self, job_id: &str, state: JobState) -> WorkerResult<()> {
+        if let Some(mut record) = self.get(job_id)? {
+            record.state = state;
+            record.updated_at = Utc::now();
+            self.put(&record)?;
+        }
+        Ok(())
+    }
+
+    /// Look up a job's record
+    pub fn get(&self, job_id: &str) -> WorkerResult<Option<JobRecord>> {
+        match self.db.get(job_id.as_bytes())? {
+            Some(value) => Ok(Some(serde_json::from_slice(&value)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Whether a job is recorded
+    pub fn contains(&self, job_id: &str) -> WorkerResult<bool> {
+        Ok(self.db.contains_key(job_id.as_bytes())?)
+    }
+
+    /// Forget a job once its result is written
+    pub fn remove(&self, job_id: &str) -> WorkerResult<()> {
+        self.db.remove(job_id.as_bytes())?;
+        self.db.flush()?;
+        Ok(())
+    }
+
+    /// All recorded jobs, oldest update first
+    ///
+    /// Unreadable records (e.g. from an incompatible version) are logged and
+    /// skipped.
+    pub fn records(&self) -> WorkerResult<Vec<JobRecord>> {
+        let mut records = Vec::new();
+        for entry in self.db.iter() {
+            let (key, value) = entry?;
+            match serde_json::from_slice::<JobRecord>(&value) {
+                Ok(record) => records.push(record),
+                Err(e) => log::warn!(
+                    "Skipping unreadable job record {}: {}",
+                    String::from_utf8_lossy(&key),
+                    e
+                ),
+            }
+        }
+        records.sort_by_key(|record| record.updated_at);
+        Ok(records)
+    }
+
+    /// Number of recorded jobs
+    pub fn len(&self) -> usize {
+        self.db.len()
+    }
+
+    /// Whether no jobs are recorded
+    pub fn is_empty(&self) -> bool {
+        self.db.is_empty()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use guestkit_job_spec::builder::JobBuilder;
+
+    fn job(id: &str, max_attempts: u32) -> JobDocument {
+        JobBuilder::new()
+            .job_id(id)
+            .operation("test.operation")
+            .payload("test.operation.v1", serde_json::json!({}))
+            .max_attempts(max_attempts)
+            .build()
+            .unwrap()
+    }
+
+    fn record(state: JobState, max_attempts: u32) -> JobRecord {
+        JobRecord {
+            job: job("job-00000001", max_attempts),
+            state,
+            worker_id: "worker-test".to_string(),
+            updated_at: Utc::now(),
+        }
+    }
+
+    #[test]
+    fn test_store_upconverts_v1_records() {
+        let store = JobStore::temporary().unwrap();
+
+        let mut value = serde_json::to_value(record(JobState::Pending, 1)).unwrap();
+        value["job"]["version"] = "1.0".into();
+        value["job"]["routing"] = serde_json::json!({ "affinity": { "zone": "a" } });
+        store.db.insert("job-00000001", serde_json::to_vec(&value).unwrap()).unwrap();
+
+        let job = store.get("job-00000001").unwrap().unwrap().job;
+        assert_eq!(job.version, guestkit_job_spec::PROTOCOL_VERSION);
+        assert_eq!(job.routing.unwrap().affinity.unwrap()["zone"], vec!["a".to_string()]);
+    }
+
+    #[test]
+    fn test_store_round_trip() {
+        let store = JobStore::temporary().unwrap();
+
+        store.insert(&job("job-00000001", 1), JobState::Pending, "worker-test").unwrap();
+        store.set_state("job-00000001", JobState::Running).unwrap();
+        assert!(store.contains("job-00000001").unwrap());
+        assert_eq!(store.get("job-00000001").unwrap().unwrap().state, JobState::Running);
+
+        // Unknown jobs are not created by a state update
+        store.set_state("job-00000002", JobState::Queued).unwrap();
+        assert_eq!(store.records().unwrap().len(), 1);
+
+        store.remove("job-00000001").unwrap();
+        assert!(store.is_empty());
+    }
+
+    #[test]
+    fn test_store_survives_reopen() {
+        let temp_dir = tempfile::TempDir::new().unwrap();
+        let path = temp_dir.path().join("jobs");
+
+        {
+            let store = JobStore::open(&path).unwrap();
+            store.insert(&job("job-00000001", 1), JobState::Running, "worker-test").unwrap();
+        }
+
+        let store = JobStore::open(&path).unwrap();
+        let records = store.records().unwrap();
+        assert_eq!(records.len(), 1);
+        assert_eq!(records[0].state, JobState::Running);
+    }
+
+    #[test]
+    fn test_recovery_actions() {
+        assert!(matches!(
+            RecoveryAction::for_record(record(JobState::Queued, 1), false),
+            RecoveryAction::Requeue(_)
+        ));
+        assert!(matches!(
+            RecoveryAction::for_record(record(JobState::Running, 1), false),
+            RecoveryAction::Fail(_)
+        ));
+        assert!(matches!(
+            RecoveryAction::for_record(record(JobState::Running, 3), true),
+            RecoveryAction::Discard(_)
+        ));
+
+        match RecoveryAction::for_record(record(JobState::Running, 3), false) {
+            RecoveryAction::Retry(job) => {
+                assert_eq!(job.execution.unwrap().attempt, 2);
+            }
+            other => panic!("expected retry, got {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,275 @@
+//! OpenTelemetry tracing for job execution
+//!
+//! With the `otel` feature enabled and [`init`] called, the job lifecycle
+//! (fetch, validate, execute, report) and the guestfs operations inside
+//! handlers are exported as spans over OTLP/HTTP. A job whose
+//! `observability` block carries `trace_id` and `span_id` is recorded as part
+//! of that trace; other jobs start a new one.
+//!
+//! Without the feature every type here is an empty no-op, so callers never
+//! need `cfg` attributes of their own.
+
+use guestkit_job_spec::{JobDocument, Observability};
+use std::fmt::Display;
+use std::time::SystemTime;
+use crate::error::{WorkerError, WorkerResult};
+
+#[cfg(feature = "otel")]
+use opentelemetry::{
+    global,
+    trace::{
+        SpanContext, SpanId, Status, TraceContextExt, TraceFlags, TraceId, TraceState,
+        Tracer,
+    },
+    Context, KeyValue,
+};
+
+/// Instrumentation scope of all worker spans
+#[cfg(feature = "otel")]
+const TRACER_NAME: &str = "guestkit-worker";
+
+/// Install the OTLP exporter
+///
+/// `endpoint` is the collector base URL (e.g. `http://localhost:4318`);
+/// spans are posted to `<endpoint>/v1/traces`. Must be called from within a
+/// Tokio runtime.
+#[cfg(feature = "otel")]
+pub fn init(endpoint: &str, service_name: &str) -> WorkerResult<()> {
+    use opentelemetry_otlp::{WithExportConfig, SpanExporter};
+    use opentelemetry_sdk::{runtime, trace::TracerProvider, Resource};
+
+    let exporter = SpanExporter::builder()
+        .with_http()
+        .with_endpoint(format!("{}/v1/traces", endpoint.trim_end_matches('/')))
+        .build()
+        .map_err(|e| WorkerError::InvalidConfig(format!("OTLP exporter: {}", e)))?;
+
+    let provider = TracerProvider::builder()
+        .with_batch_exporter(exporter, runtime::Tokio)
+        .with_resource(Resource::new(vec![KeyValue::new(
+            "service.name",
+            service_name.to_string(),
+        )]))
+        .build();
+
+    global::set_tracer_provider(provider);
+    Ok(())
+}
+
+/// Install the OTLP exporter
+#[cfg(not(feature = "otel"))]
+pub fn init(_endpoint: &str, _service_name: &str) -> WorkerResult<()> {
+    Err(WorkerError::InvalidConfig(
+        "tracing export requires guestkit-worker built with the `otel` feature".to_string(),
+    ))
+}
+
+/// Flush buffered spans and stop the exporter
+///
+/// Blocks until the exporter is done, so call it outside async code (e.g.
+/// through `spawn_blocking`).
+pub fn shutdown() {
+    #[cfg(feature = "otel")]
+    global::shutdown_tracer_provider();
+}
+
+/// Position in a trace that new spans are started under
+#[derive(Debug, Clone, Default)]
+pub struct TraceContext {
+    #[cfg(feature = "otel")]
+    cx: Context,
+}
+
+impl TraceContext {
+    /// Trace context named by a job's observability fields
+    ///
+    /// Falls back to an empty context (a new trace) when the job carries no
+    /// trace, or IDs that are not hex. Dashes are ignored so UUID trace IDs
+    /// are accepted.
+    pub fn from_job(job: &JobDocument) -> Self {
+        #[cfg(feature = "otel")]
+        {
+            let remote = job.observability.as_ref().and_then(|obs| {
+                let trace_id = TraceId::from_hex(&obs.trace_id.as_ref()?.replace('-', "")).ok()?;
+                let span_id = SpanId::from_hex(&obs.span_id.as_ref()?.replace('-', "")).ok()?;
+                let context = SpanContext::new(
+                    trace_id,
+                    span_id,
+                    TraceFlags::SAMPLED,
+                    true,
+                    TraceState::default(),
+                );
+                context.is_valid().then_some(context)
+            });
+
+            match remote {
+                Some(remote) => Self {
+                    cx: Context::new().with_remote_span_context(remote),
+                },
+                None => Self::default(),
+            }
+        }
+
+        #[cfg(not(feature = "otel"))]
+        {
+            let _ = job;
+            Self::default()
+        }
+    }
+
+    /// Start a child span, ended when the returned guard is dropped
+    pub fn span(&self, name: &'static str) -> Span {
+        self.span_since(name, SystemTime::now())
+    }
+
+    /// Start a child span that began at `start`
+    ///
+    /// Used for work whose trace is only known once it is done, like
+    /// fetching the job document itself.
+    pub fn span_since(&self, name: &'static str, start: SystemTime) -> Span {
+        #[cfg(feature = "otel")]
+        {
+            let tracer = global::tracer(TRACER_NAME);
+            let span = tracer
+                .span_builder(name)
+                .with_start_time(start)
+                .start_with_context(&tracer, &self.cx);
+            Span {
+                cx: self.cx.with_span(span),
+            }
+        }
+
+        #[cfg(not(feature = "otel"))]
+        {
+            let _ = (name, start);
+            Span {}
+        }
+    }
+
+    /// Run `f` in a child span, marking the span failed if `f` fails
+    pub fn in_span<T, E: Display>(
+        &self,
+        name: &'static str,
+        f: impl FnOnce() -> Result<T, E>,
+    ) -> Result<T, E> {
+        let span = self.span(name);
+        let result = f();
+        if let Err(ref e) = result {
+            span.record_error(e);
+        }
+        result
+    }
+
+    /// Trace and span ID of this context, for handing on to other jobs
+    pub fn observability(&self) -> Option<Observability> {
+        #[cfg(feature = "otel")]
+        {
+            let span = self.cx.span();
+            let context = span.span_context();
+            context.is_valid().then(|| Observability {
+                trace_id: Some(context.trace_id().to_string()),
+                span_id: Some(context.span_id().to_string()),
+                ..Default::default()
+            })
+        }
+
+        #[cfg(not(feature = "otel"))]
+        None
+    }
+}
+
+/// A running span
+#[derive(Debug)]
+pub struct Span {
+    #[cfg(feature = "otel")]
+    cx: Context,
+}
+
+impl Span {
+    /// Context for spans nested under this one
+    pub fn context(&self) -> TraceContext {
+        TraceContext {
+            #[cfg(feature = "otel")]
+            cx: self.cx.clone(),
+        }
+    }
+
+    /// Attach a string attribute
+    pub fn set_attribute(&self, key: &'static str, value: impl Into<String>) {
+        #[cfg(feature = "otel")]
+        self.cx.span().set_attribute(KeyValue::new(key, value.into()));
+
+        #[cfg(not(feature = "otel"))]
+        let _ = (key, value);
+    }
+
+    /// Mark the span as failed
+    pub fn record_error(&self, error: &dyn Display) {
+        #[cfg(feature = "otel")]
+        self.cx.span().set_status(Status::error(error.to_string()));
+
+        #[cfg(not(feature = "otel"))]
+        let _ = error;
+    }
+}
+
+impl Drop for Span {
+    fn drop(&mut self) {
+        #[cfg(feature = "otel")]
+        self.cx.span().end();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use guestkit_job_spec::builder::JobBuilder;
+
+    fn job(trace_id: Option<&str>, span_id: Option<&str>) -> JobDocument {
+        let mut job = JobBuilder::new()
+            .job_id("test-job-trace")
+            .operation("test.operation")
+            .payload("test.operation.v1", serde_json::json!({}))
+            .build()
+            .unwrap();
+        job.observability = Some(Observability {
+            trace_id: trace_id.map(String::from),
+            span_id: span_id.map(String::from),
+            ..Default::default()
+        });
+        job
+    }
+
+    #[test]
+    fn test_spans_without_exporter() {
+        let trace = TraceContext::from_job(&job(None, None));
+        let span = trace.span("job");
+        span.set_attribute("job.id", "test-job-trace");
+
+        let result: Result<(), String> = span.context().in_span("job.validate", || Err("bad".into()));
+        assert!(result.is_err());
+    }
+
+    #[cfg(feature = "otel")]
+    #[test]
+    fn test_trace_context_from_job() {
+        let trace = TraceContext::from_job(&job(
+            Some("550e8400-e29b-41d4-a716-446655440001"),
+            Some("446655440001"),
+        ));
+        let obs = trace.observability().unwrap();
+        assert_eq!(obs.trace_id.as_deref(), Some("550e8400e29b41d4a716446655440001"));
+        assert_eq!(obs.span_id.as_deref(), Some("0000446655440001"));
+
+        // A trace without a parent span, or with malformed IDs, starts a new one
+        assert!(TraceContext::from_job(&job(Some("550e8400"), None)).observability().is_none());
+        assert!(TraceContext::from_job(&job(Some("not-hex"), Some("1"))).observability().is_none());
+    }
+
+    #[cfg(not(feature = "otel"))]
+    #[test]
+    fn test_init_requires_feature() {
+        assert!(init("http://localhost:4318", "guestkit-worker").is_err());
+        assert!(TraceContext::from_job(&job(Some("abc"), Some("def"))).observability().is_none());
+    }
+}
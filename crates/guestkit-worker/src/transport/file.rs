@@ -1,6 +1,7 @@
 //! File-based job transport
 //!
-//! Watches a directory for new job files and processes them.
+//! Watches a directory for new job files and processes them. Workers
+//! advertise their capabilities in `<watch_dir>/workers/<worker_id>.json`.
 
 use async_trait::async_trait;
 use guestkit_job_spec::JobDocument;
@@ -11,6 +12,7 @@ use tokio::fs;
 use std::path::{Path, PathBuf};
 use std::sync::Arc;
 use std::time::Duration;
+use crate::capabilities::Capabilities;
 use crate::error::{WorkerError, WorkerResult};
 use super::JobTransport;
 
@@ -128,7 +130,7 @@ impl FileTransport {
     /// Read and parse job file
     async fn read_job(&self, path: &Path) -> WorkerResult<JobDocument> {
         let contents = fs::read_to_string(path).await?;
-        let job: JobDocument = serde_json::from_str(&contents)?;
+        let job = JobDocument::parse_any(&contents)?;
         Ok(job)
     }
 }
@@ -165,6 +167,23 @@ impl JobTransport for FileTransport {
         self.move_to_failed(job_id, reason).await
     }
 
+    async fn advertise(&mut self, worker_id: &str, capabilities: &Capabilities) -> WorkerResult<()> {
+        // Not watched: the watcher only picks up files directly in watch_dir
+        let workers_dir = self.config.watch_dir.join("workers");
+        fs::create_dir_all(&workers_dir).await?;
+
+        let advertisement = serde_json::json!({
+            "worker_id": worker_id,
+            "advertised_at": chrono::Utc::now(),
+            "capabilities": capabilities,
+        });
+        let path = workers_dir.join(format!("{}.json", worker_id));
+        fs::write(&path, serde_json::to_string_pretty(&advertisement)?).await?;
+
+        log::info!("Advertised capabilities in {}", path.display());
+        Ok(())
+    }
+
     async fn health_check(&self) -> WorkerResult<bool> {
         // Check if directories are accessible
         Ok(self.config.watch_dir.exists()
@@ -220,4 +239,33 @@ mod tests {
         assert!(config.done_dir.join("test-job-123.json").exists());
         assert!(!job_file.exists());
     }
+
+    #[tokio::test]
+    async fn test_file_transport_advertise() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let config = FileTransportConfig {
+            watch_dir: temp_dir.path().join("jobs"),
+            done_dir: temp_dir.path().join("done"),
+            failed_dir: temp_dir.path().join("failed"),
+            poll_interval_secs: 1,
+        };
+
+        let mut transport = FileTransport::new(config.clone()).await.unwrap();
+        let capabilities = Capabilities::new()
+            .with_operation("guestkit.inspect")
+            .with_disk_format("qcow2");
+
+        transport.advertise("worker-a", &capabilities).await.unwrap();
+
+        let advertised: serde_json::Value = serde_json::from_str(
+            &fs::read_to_string(config.watch_dir.join("workers/worker-a.json")).await.unwrap(),
+        )
+        .unwrap();
+        assert_eq!(advertised["worker_id"], "worker-a");
+        assert_eq!(advertised["capabilities"]["disk_formats"][0], "qcow2");
+
+        // The advertisement is not mistaken for a job
+        assert!(transport.fetch_job().await.unwrap().is_none());
+    }
 }
@@ -2,6 +2,7 @@
 
 use async_trait::async_trait;
 use guestkit_job_spec::JobDocument;
+use crate::capabilities::Capabilities;
 use crate::error::WorkerResult;
 
 pub mod file;
@@ -22,6 +23,14 @@ pub trait JobTransport: Send + Sync {
     /// Negative acknowledgement (failure/retry)
     async fn nack_job(&mut self, job_id: &str, reason: &str) -> WorkerResult<()>;
 
+    /// Publish the worker's capabilities to job producers
+    ///
+    /// Called once at startup. Transports without a way to publish them
+    /// (e.g. HTTP, which serves them from the API) do nothing.
+    async fn advertise(&mut self, _worker_id: &str, _capabilities: &Capabilities) -> WorkerResult<()> {
+        Ok(())
+    }
+
     /// Check transport health
     async fn health_check(&self) -> WorkerResult<bool> {
         Ok(true)
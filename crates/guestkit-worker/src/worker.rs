@@ -1,15 +1,28 @@
 //! Main worker daemon
 
+use std::collections::HashMap;
 use std::sync::Arc;
 use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+use guestkit::core::AuditLog;
+use guestkit_job_spec::{JobDocument, JobError, SignaturePolicy, TrustAnchors};
 use tokio::signal;
+use crate::artifacts::{ArtifactConfig, ArtifactStore};
+use crate::cancel::{CancelRegistry, ControlOutcome};
+use crate::concurrency::{ConcurrencyClass, ConcurrencyLimiter};
 use crate::error::{WorkerError, WorkerResult};
-use crate::executor::JobExecutor;
+use crate::events::EventBus;
+use crate::executor::{DependencyStatus, JobExecutor};
 use crate::handler::HandlerRegistry;
+use crate::lease::{LeaseConfig, LeaseStore};
 use crate::result::ResultWriter;
+use crate::state::JobState;
+use crate::store::{JobStore, RecoveryAction};
+use crate::scheduler::Scheduler;
 use crate::transport::JobTransport;
 use crate::capabilities::Capabilities;
 use crate::metrics::MetricsRegistry;
+use crate::telemetry::TraceContext;
 
 /// Worker configuration
 #[derive(Debug, Clone)]
@@ -26,11 +39,36 @@ pub struct WorkerConfig {
     /// Result output directory
     pub result_dir: std::path::PathBuf,
 
+    /// Persisted recurring job schedules
+    pub schedule_dir: std::path::PathBuf,
+
+    /// Persistent state of unfinished jobs and the job audit log
+    pub state_dir: std::path::PathBuf,
+
     /// Maximum concurrent jobs
     pub max_concurrent_jobs: usize,
 
+    /// Operations with their own, lower concurrency limit
+    pub concurrency_classes: Vec<ConcurrencyClass>,
+
     /// Graceful shutdown timeout (seconds)
     pub shutdown_timeout_secs: u64,
+
+    /// How long a job waits on a dependency the worker has never seen
+    /// before it is failed (seconds)
+    pub dependency_timeout_secs: u64,
+
+    /// Remote image fetch and result upload settings
+    pub artifacts: ArtifactConfig,
+
+    /// Job claiming between workers sharing a job source
+    pub lease: LeaseConfig,
+
+    /// JWK set of keys trusted to sign jobs; signatures are not checked when unset
+    pub trust_anchors: Option<std::path::PathBuf>,
+
+    /// Reject unsigned jobs (requires `trust_anchors`)
+    pub require_signed_jobs: bool,
 }
 
 impl Default for WorkerConfig {
@@ -40,8 +78,16 @@ impl Default for WorkerConfig {
             worker_pool: Some("default".to_string()),
             work_dir: std::env::temp_dir().join("guestkit-worker"),
             result_dir: std::path::PathBuf::from("./results"),
+            schedule_dir: std::path::PathBuf::from("./schedules"),
+            state_dir: std::path::PathBuf::from("./state"),
             max_concurrent_jobs: 4,
+            concurrency_classes: ConcurrencyClass::defaults(),
             shutdown_timeout_secs: 30,
+            dependency_timeout_secs: 600,
+            artifacts: ArtifactConfig::default(),
+            lease: LeaseConfig::default(),
+            trust_anchors: None,
+            require_signed_jobs: false,
         }
     }
 }
@@ -55,6 +101,14 @@ pub struct Worker {
     transport: Box<dyn JobTransport>,
     running: Arc<AtomicBool>,
     metrics: Option<Arc<MetricsRegistry>>,
+    event_bus: Option<EventBus>,
+    cancellations: CancelRegistry,
+    limiter: Arc<ConcurrencyLimiter>,
+    artifacts: Arc<ArtifactStore>,
+    store: Arc<JobStore>,
+    leases: Option<Arc<LeaseStore>>,
+    signatures: Option<Arc<SignaturePolicy>>,
+    audit_log: Arc<AuditLog>,
 }
 
 impl Worker {
@@ -66,14 +120,67 @@ impl Worker {
         transport: Box<dyn JobTransport>,
     ) -> WorkerResult<Self> {
         let registry = Arc::new(registry);
-        let result_writer = Arc::new(ResultWriter::new(&config.result_dir));
+        let artifacts = Arc::new(ArtifactStore::new(config.artifacts.clone(), &config.work_dir)?);
+        std::fs::create_dir_all(&config.state_dir)?;
+        let store = Arc::new(JobStore::open(&config.state_dir.join("jobs"))?);
+        let audit_log = Arc::new(AuditLog::new(config.state_dir.join("audit.jsonl")));
+        let leases = match config.lease.dir {
+            Some(ref dir) => Some(Arc::new(LeaseStore::new(
+                dir,
+                &config.worker_id,
+                config.lease.ttl_secs,
+            )?)),
+            None => None,
+        };
+
+        let signatures = match config.trust_anchors {
+            Some(ref path) => {
+                let policy = SignaturePolicy::new(TrustAnchors::load(path)?);
+                Some(Arc::new(if config.require_signed_jobs {
+                    policy.strict()
+                } else {
+                    policy
+                }))
+            }
+            None if config.require_signed_jobs => {
+                return Err(WorkerError::InvalidConfig(
+                    "signed jobs are required but no trust anchors are configured".to_string(),
+                ));
+            }
+            None => None,
+        };
+
+        let limiter = Arc::new(ConcurrencyLimiter::new(
+            config.max_concurrent_jobs,
+            config.concurrency_classes.clone(),
+        ));
+
+        let mut result_writer = ResultWriter::new(&config.result_dir);
+        if let Some(ref leases) = leases {
+            result_writer = result_writer.with_leases(Arc::clone(leases));
+        }
+
+        let cancellations = CancelRegistry::new();
 
-        let executor = Arc::new(JobExecutor::new(
+        let mut executor = JobExecutor::new(
             &config.worker_id,
             registry.clone(),
-            result_writer,
+            Arc::new(result_writer),
             &config.work_dir,
-        ));
+        )
+        .with_cancel_registry(cancellations.clone())
+        .with_artifact_store(Arc::clone(&artifacts))
+        .with_job_store(Arc::clone(&store))
+        .with_capabilities(Arc::new(capabilities.clone()))
+        .with_concurrency_limiter(Arc::clone(&limiter))
+        .with_audit_log(Arc::clone(&audit_log));
+        if let Some(ref leases) = leases {
+            executor = executor.with_lease_store(Arc::clone(leases));
+        }
+        if let Some(ref signatures) = signatures {
+            executor = executor.with_signature_policy(Arc::clone(signatures));
+        }
+        let executor = Arc::new(executor);
 
         Ok(Self {
             config,
@@ -83,22 +190,69 @@ impl Worker {
             transport,
             running: Arc::new(AtomicBool::new(false)),
             metrics: None,
+            event_bus: None,
+            cancellations,
+            limiter,
+            artifacts,
+            store,
+            leases,
+            signatures,
+            audit_log,
         })
     }
 
     /// Set metrics registry
     pub fn with_metrics(&mut self, metrics: Arc<MetricsRegistry>) {
-        // Update executor with metrics
-        let result_writer = Arc::new(ResultWriter::new(&self.config.result_dir));
-        let executor = JobExecutor::new(
+        self.metrics = Some(metrics);
+        self.rebuild_executor();
+    }
+
+    /// Set event bus for streaming job progress and state changes
+    pub fn with_event_bus(&mut self, event_bus: EventBus) {
+        self.event_bus = Some(event_bus);
+        self.rebuild_executor();
+    }
+
+    /// Share a cancel registry (e.g. with the REST API)
+    pub fn with_cancel_registry(&mut self, cancellations: CancelRegistry) {
+        self.cancellations = cancellations;
+        self.rebuild_executor();
+    }
+
+    /// Recreate the executor with the currently configured extensions
+    fn rebuild_executor(&mut self) {
+        let mut result_writer = ResultWriter::new(&self.config.result_dir);
+        if let Some(ref leases) = self.leases {
+            result_writer = result_writer.with_leases(Arc::clone(leases));
+        }
+
+        let mut executor = JobExecutor::new(
             &self.config.worker_id,
             self.registry.clone(),
-            result_writer,
+            Arc::new(result_writer),
             &self.config.work_dir,
-        ).with_metrics(Arc::clone(&metrics));
+        )
+        .with_cancel_registry(self.cancellations.clone())
+        .with_artifact_store(Arc::clone(&self.artifacts))
+        .with_job_store(Arc::clone(&self.store))
+        .with_capabilities(Arc::new(self.capabilities.clone()))
+        .with_concurrency_limiter(Arc::clone(&self.limiter))
+        .with_audit_log(Arc::clone(&self.audit_log));
+
+        if let Some(ref leases) = self.leases {
+            executor = executor.with_lease_store(Arc::clone(leases));
+        }
+        if let Some(ref signatures) = self.signatures {
+            executor = executor.with_signature_policy(Arc::clone(signatures));
+        }
+        if let Some(ref metrics) = self.metrics {
+            executor = executor.with_metrics(Arc::clone(metrics));
+        }
+        if let Some(ref event_bus) = self.event_bus {
+            executor = executor.with_event_bus(event_bus.clone());
+        }
 
         self.executor = Arc::new(executor);
-        self.metrics = Some(metrics);
     }
 
     /// Start the worker
@@ -106,6 +260,14 @@ impl Worker {
         log::info!("Starting worker {}", self.config.worker_id);
         log::info!("Worker pool: {:?}", self.config.worker_pool);
         log::info!("Max concurrent jobs: {}", self.config.max_concurrent_jobs);
+        for class in &self.config.concurrency_classes {
+            log::info!(
+                "Concurrency class {}: {} at a time ({})",
+                class.name,
+                class.limit,
+                class.operations.join(", ")
+            );
+        }
         log::info!("Supported operations: {:?}", self.capabilities.operations);
 
         self.running.store(true, Ordering::SeqCst);
@@ -118,28 +280,89 @@ impl Worker {
             running.store(false, Ordering::SeqCst);
         });
 
+        if let Err(e) = self
+            .transport
+            .advertise(&self.config.worker_id, &self.capabilities)
+            .await
+        {
+            log::warn!("Failed to advertise capabilities: {}", e);
+        }
+
+        if let Some(ref leases) = self.leases {
+            log::info!("Claiming jobs with {}s leases", leases.ttl().as_secs());
+            self.spawn_lease_renewal(Arc::clone(leases));
+        }
+
+        // Jobs waiting for their dependencies to finish
+        let mut pending: Vec<JobDocument> = Vec::new();
+
+        // When pending jobs started waiting on a dependency the worker has
+        // never seen
+        let mut orphaned: HashMap<String, Instant> = HashMap::new();
+
+        // Recurring job templates
+        let result_writer = Arc::new(ResultWriter::new(&self.config.result_dir));
+        let mut scheduler = Scheduler::load(&self.config.schedule_dir, result_writer.clone()).await?;
+
+        // Jobs left unfinished by a previous run
+        for job in self.recover_jobs(&result_writer).await {
+            if job.depends_on.is_empty() {
+                self.spawn_job(job);
+            } else {
+                pending.push(job);
+            }
+        }
+
         // Main event loop
         while self.running.load(Ordering::SeqCst) {
+            self.dispatch_scheduled(&mut scheduler, &mut pending).await;
+            self.dispatch_pending(&mut pending, &mut orphaned).await;
+
+            // Accepted jobs that have not started yet
+            if let Some(ref metrics) = self.metrics {
+                metrics.set_queue_depth((pending.len() + self.limiter.waiting()) as i64);
+            }
+
+            // Leave jobs at the source for other workers while busy
+            if self.limiter.is_saturated() {
+                tokio::time::sleep(tokio::time::Duration::from_millis(500)).await;
+                continue;
+            }
+
             // Fetch next job
-            match self.transport.fetch_job().await {
+            let fetch_started = std::time::SystemTime::now();
+            let fetched = self.transport.fetch_job().await;
+            if let Ok(Some(ref job)) = fetched {
+                let span = TraceContext::from_job(job).span_since("job.fetch", fetch_started);
+                span.set_attribute("job.id", job.job_id.clone());
+            }
+
+            match fetched {
+                Ok(Some(job)) if self.store.contains(&job.job_id).unwrap_or(false) => {
+                    log::info!("Job {} is already in progress, ignoring redelivery", job.job_id);
+                }
+                Ok(Some(job)) if job.execution.as_ref().is_some_and(|e| e.schedule.is_some()) => {
+                    log::info!("Received scheduled job: {}", job.job_id);
+
+                    // Runs carry the template's signature, so check it here
+                    if let Err(e) = self.executor.verify_signature(&job) {
+                        log::error!("Rejected schedule {}: {}", job.job_id, e);
+                    } else if let Err(e) = scheduler.add(job).await {
+                        log::error!("Failed to register schedule: {}", e);
+                    }
+                }
+                Ok(Some(job)) if !job.depends_on.is_empty() => {
+                    log::info!(
+                        "Received job: {} (waiting on {:?})",
+                        job.job_id,
+                        job.depends_on
+                    );
+                    self.track(&job);
+                    pending.push(job);
+                }
                 Ok(Some(job)) => {
                     log::info!("Received job: {}", job.job_id);
-
-                    // Execute job (in background for now - TODO: semaphore for concurrency)
-                    let executor = self.executor.clone();
-                    let job_id = job.job_id.clone();
-                    let mut transport = self.transport.as_mut();
-
-                    tokio::spawn(async move {
-                        match executor.execute(job).await {
-                            Ok(_) => {
-                                log::info!("Job {} completed", job_id);
-                            }
-                            Err(e) => {
-                                log::error!("Job {} failed: {}", job_id, e);
-                            }
-                        }
-                    });
+                    self.spawn_job(job);
                 }
                 Ok(None) => {
                     // No jobs available, continue polling
@@ -159,6 +382,235 @@ impl Worker {
         Ok(())
     }
 
+    /// Keep held leases alive, cancelling jobs whose lease was taken over
+    fn spawn_lease_renewal(&self, leases: Arc<LeaseStore>) {
+        let running = self.running.clone();
+        let cancellations = self.cancellations.clone();
+
+        tokio::spawn(async move {
+            // Renew well before expiry so a slow renewal does not lose the lease
+            let mut interval = tokio::time::interval(leases.ttl() / 3);
+            while running.load(Ordering::SeqCst) {
+                interval.tick().await;
+                for job_id in leases.renew_all().await {
+                    log::error!("Lease on job {} was taken over by another worker, cancelling it", job_id);
+                    cancellations.cancel(&job_id);
+                }
+            }
+        });
+    }
+
+    /// Record a job that is accepted but not yet handed to the executor
+    ///
+    /// The job can be cancelled from here on.
+    fn track(&self, job: &JobDocument) {
+        self.cancellations.register(&job.job_id);
+        if let Err(e) = self.store.insert(job, JobState::Pending, &self.config.worker_id) {
+            log::warn!("Failed to persist job {}: {}", job.job_id, e);
+        }
+    }
+
+    /// Re-queue or fail jobs left unfinished by a previous run
+    ///
+    /// Returns the jobs to run again. Jobs that were running with no attempts
+    /// left get a failure result so clients waiting on them are not stuck.
+    async fn recover_jobs(&self, result_writer: &ResultWriter) -> Vec<JobDocument> {
+        let records = match self.store.records() {
+            Ok(records) => records,
+            Err(e) => {
+                log::error!("Cannot read job store: {}", e);
+                return Vec::new();
+            }
+        };
+        if records.is_empty() {
+            return Vec::new();
+        }
+
+        log::info!("Recovering {} unfinished job(s)", records.len());
+        let mut jobs = Vec::new();
+
+        for record in records {
+            let job_id = record.job.job_id.clone();
+            let has_result = result_writer.result_exists(&job_id).await;
+
+            match RecoveryAction::for_record(record, has_result) {
+                RecoveryAction::Requeue(job) => {
+                    log::info!("Re-queueing job {}", job_id);
+                    self.track(&job);
+                    jobs.push(job);
+                }
+                RecoveryAction::Retry(job) => {
+                    log::warn!(
+                        "Job {} was running when the worker stopped, retrying (attempt {})",
+                        job_id,
+                        job.execution.as_ref().map(|e| e.attempt).unwrap_or(1)
+                    );
+                    self.track(&job);
+                    jobs.push(job);
+                }
+                RecoveryAction::Fail(record) => {
+                    log::error!("Job {} was running when the worker stopped, failing it", job_id);
+                    let written = result_writer
+                        .write_failure(
+                            &job_id,
+                            &self.config.worker_id,
+                            record.updated_at,
+                            record.job.execution.as_ref().map(|e| e.attempt).unwrap_or(1),
+                            "WORKER_RESTARTED",
+                            format!(
+                                "Worker {} stopped while the job was {}",
+                                record.worker_id, record.state
+                            ),
+                            Some("recovery".to_string()),
+                            true,
+                            None,
+                        )
+                        .await;
+                    match written {
+                        Ok(_) => {
+                            let _ = self.store.remove(&job_id);
+                        }
+                        Err(e) => log::error!("Failed to record failure of job {}: {}", job_id, e),
+                    }
+                }
+                RecoveryAction::Discard(_) => {
+                    log::info!("Job {} finished before the worker stopped", job_id);
+                    let _ = self.store.remove(&job_id);
+                }
+            }
+        }
+
+        jobs
+    }
+
+    /// Start jobs for schedules that are due
+    async fn dispatch_scheduled(&self, scheduler: &mut Scheduler, pending: &mut Vec<JobDocument>) {
+        let runs = match scheduler.due(chrono::Utc::now()).await {
+            Ok(runs) => runs,
+            Err(e) => {
+                log::error!("Scheduler error: {}", e);
+                return;
+            }
+        };
+
+        for run in runs {
+            if let Some(previous) = run.replaces {
+                if self.cancellations.cancel(&previous) == ControlOutcome::Applied {
+                    log::info!(
+                        "Cancelled job {} replaced by schedule {}",
+                        previous,
+                        run.schedule_id
+                    );
+                }
+            }
+
+            self.executor.trust_scheduled_run(&run.job.job_id);
+            if run.job.depends_on.is_empty() {
+                self.spawn_job(run.job);
+            } else {
+                self.cancellations.register(&run.job.job_id);
+                pending.push(run.job);
+            }
+        }
+    }
+
+    /// Start pending jobs whose dependencies have resolved
+    ///
+    /// Jobs are visited in dependency order. Jobs whose dependencies failed,
+    /// that form a cycle, that were cancelled, or that waited longer than
+    /// the dependency timeout on a job the worker has never seen are handed
+    /// to the executor too, so it records the terminal result.
+    async fn dispatch_pending(
+        &self,
+        pending: &mut Vec<JobDocument>,
+        orphaned: &mut HashMap<String, Instant>,
+    ) {
+        if pending.is_empty() {
+            return;
+        }
+
+        let timeout = Duration::from_secs(self.config.dependency_timeout_secs);
+
+        let runnable: Vec<String> = match guestkit_job_spec::topological_order(pending) {
+            Ok(ordered) => {
+                let mut runnable = Vec::new();
+                for job in ordered {
+                    let waiting = match self.executor.dependency_status(job).await {
+                        DependencyStatus::Waiting(deps) => deps,
+                        _ => Vec::new(),
+                    };
+                    if waiting.is_empty() || self.cancellations.is_cancelled(&job.job_id) {
+                        runnable.push(job.job_id.clone());
+                        continue;
+                    }
+
+                    // Dependencies that are pending, running or persisted
+                    // will finish eventually; others may never arrive
+                    let unknown: Vec<&String> = waiting
+                        .iter()
+                        .filter(|dep| {
+                            !pending.iter().any(|p| &p.job_id == *dep)
+                                && !self.store.contains(dep).unwrap_or(false)
+                        })
+                        .collect();
+                    if unknown.is_empty() {
+                        orphaned.remove(&job.job_id);
+                        continue;
+                    }
+
+                    let since = *orphaned.entry(job.job_id.clone()).or_insert_with(Instant::now);
+                    if since.elapsed() >= timeout {
+                        log::error!(
+                            "Job {} waited {}s for unknown dependencies {:?}, failing it",
+                            job.job_id,
+                            timeout.as_secs(),
+                            unknown
+                        );
+                        runnable.push(job.job_id.clone());
+                    }
+                }
+                runnable
+            }
+            Err(JobError::DependencyCycle(cycle)) => {
+                log::error!("Dependency cycle between pending jobs: {}", cycle.join(" -> "));
+                cycle
+            }
+            Err(e) => {
+                log::error!("Cannot order pending jobs: {}", e);
+                pending.iter().map(|job| job.job_id.clone()).collect()
+            }
+        };
+
+        for job_id in runnable {
+            orphaned.remove(&job_id);
+            if let Some(pos) = pending.iter().position(|job| job.job_id == job_id) {
+                let job = pending.remove(pos);
+                log::info!("Dependencies resolved for job {}", job.job_id);
+                self.spawn_job(job);
+            }
+        }
+    }
+
+    /// Execute a job in the background
+    ///
+    /// The executor holds the job until a slot of its concurrency class is
+    /// free.
+    fn spawn_job(&self, job: JobDocument) {
+        let executor = self.executor.clone();
+        let job_id = job.job_id.clone();
+
+        tokio::spawn(async move {
+            match executor.execute(job).await {
+                Ok(_) => {
+                    log::info!("Job {} completed", job_id);
+                }
+                Err(e) => {
+                    log::error!("Job {} failed: {}", job_id, e);
+                }
+            }
+        });
+    }
+
     /// Get worker capabilities
     pub fn capabilities(&self) -> &Capabilities {
         &self.capabilities
@@ -214,6 +666,7 @@ mod tests {
             worker_id: "test-worker".to_string(),
             work_dir: temp_dir.path().to_path_buf(),
             result_dir: temp_dir.path().join("results"),
+            state_dir: temp_dir.path().join("state"),
             ..Default::default()
         };
 
@@ -240,4 +693,50 @@ mod tests {
 
         assert!(worker.is_ok());
     }
+
+    #[tokio::test]
+    async fn test_unknown_dependency_times_out() {
+        let temp_dir = TempDir::new().unwrap();
+
+        let config = WorkerConfig {
+            worker_id: "test-worker".to_string(),
+            work_dir: temp_dir.path().to_path_buf(),
+            result_dir: temp_dir.path().join("results"),
+            state_dir: temp_dir.path().join("state"),
+            dependency_timeout_secs: 0,
+            ..Default::default()
+        };
+
+        let transport = FileTransport::new(FileTransportConfig {
+            watch_dir: temp_dir.path().join("jobs"),
+            done_dir: temp_dir.path().join("done"),
+            failed_dir: temp_dir.path().join("failed"),
+            poll_interval_secs: 1,
+        })
+        .await
+        .unwrap();
+
+        let worker = Worker::new(
+            config,
+            Capabilities::new(),
+            HandlerRegistry::new(),
+            Box::new(transport),
+        )
+        .unwrap();
+
+        let job = guestkit_job_spec::builder::JobBuilder::new()
+            .job_id("job-00000002")
+            .operation("test.operation")
+            .payload("test.operation.v1", serde_json::json!({}))
+            .depends_on("job-00000001")
+            .build()
+            .unwrap();
+
+        let mut pending = vec![job];
+        let mut orphaned = HashMap::new();
+        worker.dispatch_pending(&mut pending, &mut orphaned).await;
+
+        assert!(pending.is_empty());
+        assert!(orphaned.is_empty());
+    }
 }
@@ -53,7 +53,7 @@ Development files and headers for %{name}.
 %build
 # Build with release profile
 export CARGO_TARGET_DIR=target
-cargo build --release --locked
+cargo build --release --locked --features oci,publish,notify,io-uring,yara,rego
 
 %install
 # Install binary
@@ -0,0 +1,41 @@
+# guestctl user-facing messages (German)
+
+## Report output
+
+written-example-policy = ✅ Beispielrichtlinie geschrieben nach: { $path }
+written-validation-report = ✅ Validierungsbericht geschrieben nach: { $path }
+written-attribution = ✅ Lizenzhinweise geschrieben nach: { $path }
+written-license-report = ✅ Lizenzbericht geschrieben nach: { $path }
+written-blueprint = ✅ Blueprint geschrieben nach: { $path }
+written-migration-plan = ✅ Migrationsplan geschrieben nach: { $path }
+written-cost-analysis = ✅ Kostenanalyse geschrieben nach: { $path }
+written-dependency-graph = ✅ Abhängigkeitsgraph geschrieben nach: { $path }
+written-permissions-report = ✅ Berechtigungsbericht geschrieben nach: { $path }
+written-crash-report = ✅ Absturzbericht geschrieben nach: { $path }
+written-support-bundle = ✅ Support-Paket geschrieben nach: { $path }
+written-triage-package = ✅ Triage-Paket geschrieben nach: { $path }
+written-recovered-files = ✅ { $files } wiederhergestellte Dateien geschrieben nach: { $path }
+written-package-history = ✅ Paketverlauf geschrieben nach: { $path }
+written-pristine-report = ✅ Integritätsbericht geschrieben nach: { $path }
+written-lint-report = ✅ Prüfbericht geschrieben nach: { $path }
+written-provenance-report = ✅ Herkunftsbericht geschrieben nach: { $path }
+written-provenance-baseline = ✅ Referenzstand mit { $files } Dateien geschrieben nach: { $path }
+written-fleet-report = ✅ Flottenbericht für { $images } Images geschrieben nach: { $path }
+
+## doctor
+
+doctor-title = 🩺 Voraussetzungen des Hosts
+doctor-fix = Behebung: { $fix }
+doctor-summary = { $passed } bestanden, { $warnings ->
+    [one] 1 Warnung
+   *[other] { $warnings } Warnungen
+}, { $failures ->
+    [one] 1 Fehler
+   *[other] { $failures } Fehler
+}
+doctor-ready = ✅ Der Host ist bereit
+doctor-usable = Der Host ist nutzbar; Warnungen betreffen optionale Funktionen
+doctor-failed = { $failures ->
+    [one] 1 Prüfung der Host-Voraussetzungen fehlgeschlagen
+   *[other] { $failures } Prüfungen der Host-Voraussetzungen fehlgeschlagen
+}
@@ -0,0 +1,44 @@
+# guestctl user-facing messages (English, reference catalog)
+#
+# Every message id must exist here; other locales fall back to this
+# catalog for ids they do not define.
+
+## Report output
+
+written-example-policy = ✅ Example policy written to: { $path }
+written-validation-report = ✅ Validation report written to: { $path }
+written-attribution = ✅ Attribution notices written to: { $path }
+written-license-report = ✅ License report written to: { $path }
+written-blueprint = ✅ Blueprint written to: { $path }
+written-migration-plan = ✅ Migration plan written to: { $path }
+written-cost-analysis = ✅ Cost analysis written to: { $path }
+written-dependency-graph = ✅ Dependency graph written to: { $path }
+written-permissions-report = ✅ Permissions report written to: { $path }
+written-crash-report = ✅ Crash report written to: { $path }
+written-support-bundle = ✅ Support bundle written to: { $path }
+written-triage-package = ✅ Triage package written to: { $path }
+written-recovered-files = ✅ { $files } recovered files written to: { $path }
+written-package-history = ✅ Package history written to: { $path }
+written-pristine-report = ✅ Pristine report written to: { $path }
+written-lint-report = ✅ Lint report written to: { $path }
+written-provenance-report = ✅ Provenance report written to: { $path }
+written-provenance-baseline = ✅ Baseline of { $files } files written to: { $path }
+written-fleet-report = ✅ Fleet report for { $images } images written to: { $path }
+
+## doctor
+
+doctor-title = 🩺 Host Prerequisites
+doctor-fix = fix: { $fix }
+doctor-summary = { $passed } passed, { $warnings ->
+    [one] 1 warning
+   *[other] { $warnings } warnings
+}, { $failures ->
+    [one] 1 failure
+   *[other] { $failures } failures
+}
+doctor-ready = ✅ Host is ready
+doctor-usable = Host is usable; warnings affect optional features
+doctor-failed = { $failures ->
+    [one] 1 host prerequisite check failed
+   *[other] { $failures } host prerequisite checks failed
+}
@@ -0,0 +1,41 @@
+# guestctl user-facing messages (Spanish)
+
+## Report output
+
+written-example-policy = ✅ Política de ejemplo escrita en: { $path }
+written-validation-report = ✅ Informe de validación escrito en: { $path }
+written-attribution = ✅ Avisos de atribución escritos en: { $path }
+written-license-report = ✅ Informe de licencias escrito en: { $path }
+written-blueprint = ✅ Blueprint escrito en: { $path }
+written-migration-plan = ✅ Plan de migración escrito en: { $path }
+written-cost-analysis = ✅ Análisis de costes escrito en: { $path }
+written-dependency-graph = ✅ Grafo de dependencias escrito en: { $path }
+written-permissions-report = ✅ Informe de permisos escrito en: { $path }
+written-crash-report = ✅ Informe de fallos escrito en: { $path }
+written-support-bundle = ✅ Paquete de soporte escrito en: { $path }
+written-triage-package = ✅ Paquete de triaje escrito en: { $path }
+written-recovered-files = ✅ { $files } archivos recuperados escritos en: { $path }
+written-package-history = ✅ Historial de paquetes escrito en: { $path }
+written-pristine-report = ✅ Informe de integridad escrito en: { $path }
+written-lint-report = ✅ Informe de comprobación escrito en: { $path }
+written-provenance-report = ✅ Informe de procedencia escrito en: { $path }
+written-provenance-baseline = ✅ Línea base de { $files } archivos escrita en: { $path }
+written-fleet-report = ✅ Informe de flota de { $images } imágenes escrito en: { $path }
+
+## doctor
+
+doctor-title = 🩺 Requisitos del sistema anfitrión
+doctor-fix = solución: { $fix }
+doctor-summary = { $passed } correctas, { $warnings ->
+    [one] 1 advertencia
+   *[other] { $warnings } advertencias
+}, { $failures ->
+    [one] 1 fallo
+   *[other] { $failures } fallos
+}
+doctor-ready = ✅ El anfitrión está listo
+doctor-usable = El anfitrión es utilizable; las advertencias afectan a funciones opcionales
+doctor-failed = { $failures ->
+    [one] Falló 1 comprobación de requisitos del anfitrión
+   *[other] Fallaron { $failures } comprobaciones de requisitos del anfitrión
+}
@@ -1,6 +1,8 @@
 // SPDX-License-Identifier: LGPL-3.0-or-later
 //! AI assistant implementation using Rig
 
+use crate::cli::plain;
+use crate::cli::target;
 use super::tools::DiagnosticTools;
 use anyhow::{Context, Result};
 use colored::Colorize;
@@ -60,19 +62,21 @@ pub fn run_ai_assistant(image_path: &Path, query: &str) -> Result<()> {
         );
     }
 
-    println!("\n{}", "╔═══════════════════════════════════════════════════╗".cyan());
-    println!("{}", "║     GuestKit AI Assistant - VM Diagnostics      ║".cyan().bold());
-    println!("{}", "╚═══════════════════════════════════════════════════╝".cyan());
+    println!("\n{}", plain::text("╔═══════════════════════════════════════════════════╗").cyan());
+    println!("{}", plain::text("║     GuestKit AI Assistant - VM Diagnostics      ║").cyan().bold());
+    println!("{}", plain::text("╚═══════════════════════════════════════════════════╝").cyan());
     println!();
     println!("{} {}", "Query:".yellow().bold(), query);
     println!();
-    println!("{} Initializing VM inspection...", "→".cyan());
+    println!("{} Initializing VM inspection...", plain::text("→").cyan());
 
     // Initialize guestfs
     let mut guestfs = Guestfs::new().context("Failed to create Guestfs handle")?;
     guestfs
         .add_drive_opts(image_path.to_str().unwrap(), false, None)
         .context("Failed to add drive")?;
+    target::add_disks(&mut guestfs, false)?;
+    target::select(&mut guestfs);
     guestfs.launch().context("Failed to launch guestfs")?;
 
     // Inspect and mount
@@ -82,7 +86,7 @@ pub fn run_ai_assistant(image_path: &Path, query: &str) -> Result<()> {
     }
 
     let root = &roots[0];
-    println!("{} Detected OS: {}", "✓".green(), root.yellow());
+    println!("{} Detected OS: {}", plain::text("✓").green(), root.yellow());
 
     // Mount filesystems
     let mounts = guestfs
@@ -92,7 +96,7 @@ pub fn run_ai_assistant(image_path: &Path, query: &str) -> Result<()> {
         let _ = guestfs.mount(&device, &mountpoint);
     }
 
-    println!("{} VM filesystem mounted", "✓".green());
+    println!("{} VM filesystem mounted", plain::text("✓").green());
     println!();
 
     // Create diagnostic tools
@@ -100,30 +104,30 @@ pub fn run_ai_assistant(image_path: &Path, query: &str) -> Result<()> {
 
     // For now, use a simple implementation without full agent framework
     // This is a MVP - full Rig agent integration can come later
-    println!("{} Analyzing VM...", "🤖".bold());
+    println!("{} Analyzing VM...", plain::text("🤖").bold());
     println!();
 
     // Gather diagnostic information based on query type
     let context = gather_diagnostic_context(&mut tools, query)?;
 
-    println!("{} Consulting AI...", "→".cyan());
+    println!("{} Consulting AI...", plain::text("→").cyan());
     println!();
 
     // Call OpenAI
     let response = call_openai_simple(query, &context)?;
 
     // Display response
-    println!("{}", "═".repeat(70).cyan());
+    println!("{}", plain::text("═").repeat(70).cyan());
     println!("{}", "AI Analysis".yellow().bold());
-    println!("{}", "═".repeat(70).cyan());
+    println!("{}", plain::text("═").repeat(70).cyan());
     println!();
     println!("{}", response);
     println!();
-    println!("{}", "═".repeat(70).cyan());
+    println!("{}", plain::text("═").repeat(70).cyan());
     println!();
 
-    println!("{} Review suggestions carefully before applying", "⚠".yellow().bold());
-    println!("{} Test in a non-production environment first", "💡".cyan());
+    println!("{} Review suggestions carefully before applying", plain::text("⚠").yellow().bold());
+    println!("{} Test in a non-production environment first", plain::text("💡").cyan());
     println!();
 
     // Cleanup
@@ -0,0 +1,290 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Align-check command - partition alignment and sector size analysis
+
+use crate::cli::audit_log::audited;
+use crate::cli::catalog::parse_image_ref;
+use crate::cli::output::format_size;
+use crate::cli::plain;
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use colored::*;
+use guestkit::disk::alignment::{self, AlignmentReport, PartitionMove};
+use guestkit::disk::{DiskReader, LoopDevice, NbdDevice};
+use guestkit::lint::Severity;
+use serde_json::json;
+use std::io::Write;
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+
+#[derive(Debug, Args)]
+pub struct AlignCheckCommand {
+    /// Disk image or block device
+    #[arg(value_parser = parse_image_ref)]
+    pub image: PathBuf,
+
+    /// Output format (text, json)
+    #[arg(short, long, default_value = "text")]
+    pub format: String,
+
+    /// Move misaligned partitions to 1 MiB boundaries
+    #[arg(long)]
+    pub realign: bool,
+
+    /// Show the moves --realign would make without changing the image
+    #[arg(long, requires = "realign")]
+    pub dry_run: bool,
+}
+
+/// The image attached as a block device, detached on drop
+pub(crate) enum Attached {
+    Loop(LoopDevice),
+    Nbd(NbdDevice),
+}
+
+impl Attached {
+    pub(crate) fn attach(image: &Path, read_only: bool) -> Result<Self> {
+        let attached = if LoopDevice::is_format_supported(image) {
+            let mut device = LoopDevice::new()?;
+            device.connect(image, read_only)?;
+            Attached::Loop(device)
+        } else {
+            let mut device = NbdDevice::new()?;
+            device.connect(image, read_only)?;
+            Attached::Nbd(device)
+        };
+        Ok(attached)
+    }
+
+    pub(crate) fn device_path(&self) -> Result<&Path> {
+        match self {
+            Attached::Loop(device) => device.device_path().context("Loop device not connected"),
+            Attached::Nbd(device) => Ok(device.device_path()),
+        }
+    }
+
+    pub(crate) fn partition_path(&self, number: u32) -> Result<PathBuf> {
+        match self {
+            Attached::Loop(device) => device
+                .partition_path(number)
+                .context("Loop device not connected"),
+            Attached::Nbd(device) => Ok(device.partition_path(number)),
+        }
+    }
+}
+
+impl AlignCheckCommand {
+    pub fn execute(&self) -> Result<()> {
+        let report = self
+            .analyze()
+            .with_context(|| format!("Failed to analyze {}", self.image.display()))?;
+
+        if self.format == "json" && !self.realign {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            return Ok(());
+        }
+        if self.format != "json" {
+            print_report(&self.image, &report);
+        }
+        if !self.realign {
+            return Ok(());
+        }
+
+        let moves = report.realignment_plan()?;
+        if self.format == "json" {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&json!({ "report": report, "moves": moves }))?
+            );
+        } else {
+            print_plan(&report, &moves);
+        }
+        if moves.is_empty() || self.dry_run {
+            return Ok(());
+        }
+
+        let parameters = json!({ "moves": moves });
+        audited("align-realign", &self.image, parameters, || {
+            self.realign(&moves)
+        })?;
+        eprintln!(
+            "{} Moved {} partition(s) to 1 MiB boundaries",
+            plain::text("✓").green(),
+            moves.len()
+        );
+        Ok(())
+    }
+
+    /// Raw images and block devices are read directly, others through NBD
+    fn analyze(&self) -> Result<AlignmentReport> {
+        let block_device = std::fs::metadata(&self.image)
+            .map(|metadata| metadata.file_type().is_block_device())
+            .unwrap_or(false);
+        if block_device || LoopDevice::is_format_supported(&self.image) {
+            return Ok(AlignmentReport::analyze(&mut DiskReader::open(
+                &self.image,
+            )?)?);
+        }
+
+        let attached = Attached::attach(&self.image, true)?;
+        let mut reader = DiskReader::open(attached.device_path()?)?;
+        Ok(AlignmentReport::analyze(&mut reader)?)
+    }
+
+    fn realign(&self, moves: &[PartitionMove]) -> Result<()> {
+        let attached = Attached::attach(&self.image, false)?;
+        let device = attached.device_path()?;
+
+        // Partitions only move towards the end, so start with the last one
+        for partition_move in moves.iter().rev() {
+            eprintln!(
+                "Moving partition {} ({})...",
+                partition_move.number,
+                format_size(partition_move.size)
+            );
+            move_partition(device, partition_move)?;
+            if alignment::update_hidden_sectors(device, partition_move.to)? {
+                log::info!(
+                    "Updated the boot sector of partition {}",
+                    partition_move.number
+                );
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Move a partition and its data with sfdisk, keeping its size
+fn move_partition(device: &Path, partition_move: &PartitionMove) -> Result<()> {
+    let mut child = Command::new("sfdisk")
+        .arg("--no-reread")
+        .arg("--no-tell-kernel")
+        .arg("--move-data")
+        .arg("-N")
+        .arg(partition_move.number.to_string())
+        .arg(device)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("Failed to execute sfdisk. Is util-linux installed?")?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        writeln!(
+            stdin,
+            "{},{}",
+            partition_move.to / 512,
+            partition_move.size / 512
+        )?;
+    }
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        bail!(
+            "sfdisk failed to move partition {}: {}",
+            partition_move.number,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+fn print_report(image: &Path, report: &AlignmentReport) {
+    println!("{}", image.display().to_string().bold());
+    println!(
+        "  Table:    {} ({}-byte sectors)",
+        report.table_type, report.table_sector_size
+    );
+    println!(
+        "  Sectors:  {} logical / {} physical{}",
+        report.logical_sector_size,
+        report.physical_sector_size,
+        if report.block_device {
+            ""
+        } else {
+            " (image: QEMU default on 4K storage)"
+        }
+    );
+    println!("  Size:     {}", format_size(report.disk_size));
+    println!();
+
+    if !report.partitions.is_empty() {
+        println!(
+            "  {:>3}  {:>14}  {:>10}  {:<8} {}",
+            "#".bold(),
+            "Start".bold(),
+            "Size".bold(),
+            "Type".bold(),
+            "Alignment".bold()
+        );
+        for partition in &report.partitions {
+            let alignment = if partition.extended {
+                "extended".normal()
+            } else if partition.aligned {
+                "1 MiB".green()
+            } else if partition.physically_aligned {
+                "4 KiB".yellow()
+            } else {
+                "misaligned".red()
+            };
+            println!(
+                "  {:>3}  {:>14}  {:>10}  {:<8} {}",
+                partition.number,
+                partition.start,
+                format_size(partition.size),
+                partition.filesystem.as_deref().unwrap_or("-"),
+                alignment
+            );
+        }
+        println!();
+    }
+
+    if report.issues.is_empty() {
+        println!("{} No alignment issues", plain::text("✓").green());
+        return;
+    }
+    for issue in &report.issues {
+        let marker = match issue.severity {
+            Severity::Error => plain::text("✗").red(),
+            Severity::Warning => "!".yellow(),
+        };
+        match issue.partition {
+            Some(number) => println!("{} partition {}: {}", marker, number, issue.message),
+            None => println!("{} {}", marker, issue.message),
+        }
+        if let Some(suggestion) = &issue.suggestion {
+            println!("    {} {}", plain::text("→").bright_blue(), suggestion);
+        }
+    }
+}
+
+fn print_plan(report: &AlignmentReport, moves: &[PartitionMove]) {
+    println!();
+    if moves.is_empty() {
+        println!("{} Nothing to realign", plain::text("✓").green());
+        return;
+    }
+
+    println!("{}", "Realignment plan:".bold());
+    for partition_move in moves {
+        println!(
+            "  partition {}: {} -> {} ({})",
+            partition_move.number,
+            partition_move.from,
+            partition_move.to,
+            format_size(partition_move.size)
+        );
+    }
+
+    // Windows finds its boot and system volumes by partition offset
+    let ntfs_moved = report.partitions.iter().any(|partition| {
+        partition.filesystem.as_deref() == Some("ntfs")
+            && moves.iter().any(|m| m.number == partition.number)
+    });
+    if ntfs_moved {
+        println!(
+            "{} Windows refers to partitions by offset (BCD store, MountedDevices); \
+             repair the boot configuration with bcdboot if the guest does not start",
+            "!".yellow()
+        );
+    }
+}
@@ -0,0 +1,259 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! MITRE ATT&CK technique catalog
+//!
+//! The bundled catalog lists the Enterprise techniques a disk image can
+//! hold evidence of, the guest paths `hunt` looks at for each, and the
+//! findings of `malware`, `secrets` and `intelligence` that indicate it.
+//! Detections are matched by ID; a trailing `/*` matches every ID below a
+//! prefix, e.g. `secrets/*`.
+
+pub mod navigator;
+
+use anyhow::{bail, Result};
+use serde::Deserialize;
+use std::sync::OnceLock;
+
+pub use navigator::Layer;
+
+const CATALOG: &str = include_str!("catalog.toml");
+
+/// Enterprise tactics in kill-chain order, by Navigator short name
+pub const TACTICS: &[(&str, &str)] = &[
+    ("initial-access", "Initial Access"),
+    ("execution", "Execution"),
+    ("persistence", "Persistence"),
+    ("privilege-escalation", "Privilege Escalation"),
+    ("defense-evasion", "Defense Evasion"),
+    ("credential-access", "Credential Access"),
+    ("discovery", "Discovery"),
+    ("lateral-movement", "Lateral Movement"),
+    ("collection", "Collection"),
+    ("command-and-control", "Command and Control"),
+    ("exfiltration", "Exfiltration"),
+    ("impact", "Impact"),
+];
+
+/// Short name of a tactic, accepting display names, underscores
+/// and `command-control`
+pub fn tactic(name: &str) -> Option<&'static str> {
+    let name = name.trim().to_ascii_lowercase().replace(['_', ' '], "-");
+    let name = match name.as_str() {
+        "command-control" | "c2" => "command-and-control",
+        other => other,
+    };
+    TACTICS
+        .iter()
+        .find(|(short, _)| *short == name)
+        .map(|(short, _)| *short)
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Catalog {
+    /// ATT&CK release the catalog follows
+    pub version: String,
+    pub techniques: Vec<Technique>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Technique {
+    /// e.g. `T1053.003`
+    pub id: String,
+    pub name: String,
+    /// Tactic short names, the primary one first
+    pub tactics: Vec<String>,
+    /// Guest paths holding evidence; `*` matches one path component
+    #[serde(default)]
+    pub artifacts: Vec<String>,
+    /// Finding IDs of other commands that indicate the technique
+    #[serde(default)]
+    pub detections: Vec<String>,
+}
+
+impl Technique {
+    /// Whether a finding with this ID indicates the technique
+    pub fn detected_by(&self, detection: &str) -> bool {
+        self.detections.iter().any(|d| match d.strip_suffix("/*") {
+            Some(prefix) => detection
+                .strip_prefix(prefix)
+                .is_some_and(|rest| rest.starts_with('/')),
+            None => d == detection,
+        })
+    }
+
+    /// Whether `id` names this technique or its parent
+    fn is(&self, id: &str) -> bool {
+        self.id.eq_ignore_ascii_case(id)
+            || self
+                .id
+                .split_once('.')
+                .is_some_and(|(parent, _)| parent.eq_ignore_ascii_case(id))
+    }
+}
+
+impl Catalog {
+    /// The catalog built into guestctl
+    pub fn bundled() -> &'static Catalog {
+        static CATALOG_CELL: OnceLock<Catalog> = OnceLock::new();
+        CATALOG_CELL.get_or_init(|| toml::from_str(CATALOG).expect("bundled ATT&CK catalog"))
+    }
+
+    /// Techniques a finding indicates
+    pub fn detected_by(&self, detection: &str) -> Vec<&Technique> {
+        self.techniques
+            .iter()
+            .filter(|t| t.detected_by(detection))
+            .collect()
+    }
+
+    /// Techniques to hunt, each with the tactic to report it under
+    ///
+    /// Filters are tactic names or technique IDs; a parent ID such as
+    /// `T1053` selects its sub-techniques. Without filters every technique
+    /// is selected under its primary tactic.
+    pub fn select(&self, filters: &[String]) -> Result<Vec<(&'static str, &Technique)>> {
+        fn push<'c>(
+            selected: &mut Vec<(&'static str, &'c Technique)>,
+            tactic: &'static str,
+            technique: &'c Technique,
+        ) {
+            if !selected
+                .iter()
+                .any(|(ta, te)| *ta == tactic && te.id == technique.id)
+            {
+                selected.push((tactic, technique));
+            }
+        }
+
+        let mut selected = Vec::new();
+
+        if filters.is_empty() {
+            for technique in &self.techniques {
+                if let Some(primary) = technique.tactics.first().and_then(|t| tactic(t)) {
+                    push(&mut selected, primary, technique);
+                }
+            }
+        }
+        for filter in filters {
+            if let Some(short) = tactic(filter) {
+                for technique in self
+                    .techniques
+                    .iter()
+                    .filter(|t| t.tactics.iter().any(|ta| ta == short))
+                {
+                    push(&mut selected, short, technique);
+                }
+                continue;
+            }
+            let matched: Vec<&Technique> = self
+                .techniques
+                .iter()
+                .filter(|t| t.is(filter.trim()))
+                .collect();
+            if matched.is_empty() {
+                bail!(
+                    "Unknown tactic or technique: {} (tactics: {})",
+                    filter,
+                    TACTICS
+                        .iter()
+                        .map(|(short, _)| *short)
+                        .collect::<Vec<_>>()
+                        .join(", ")
+                );
+            }
+            for technique in matched {
+                if let Some(primary) = technique.tactics.first().and_then(|t| tactic(t)) {
+                    push(&mut selected, primary, technique);
+                }
+            }
+        }
+
+        selected.sort_by_key(|(tactic, _)| TACTICS.iter().position(|(short, _)| short == tactic));
+        Ok(selected)
+    }
+
+    /// A Navigator layer scoring the techniques the findings indicate
+    ///
+    /// Findings are `(detection ID, severity, guest path)`.
+    pub fn layer<'a>(
+        &self,
+        name: &str,
+        description: &str,
+        findings: impl IntoIterator<Item = (&'a str, &'a str, &'a str)>,
+    ) -> Layer {
+        let mut layer = Layer::new(name, description, &self.version);
+        for (detection, severity, path) in findings {
+            for technique in self.detected_by(detection) {
+                layer.add(
+                    &technique.id,
+                    navigator::severity_score(severity),
+                    &format!("{}: {}", detection, path),
+                );
+            }
+        }
+        layer
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundled_catalog() {
+        let catalog = Catalog::bundled();
+        for technique in &catalog.techniques {
+            assert!(technique.id.starts_with('T'), "{}", technique.id);
+            assert!(!technique.tactics.is_empty(), "{}", technique.id);
+            for t in &technique.tactics {
+                assert_eq!(tactic(t), Some(t.as_str()), "{}", technique.id);
+            }
+        }
+        let mut ids: Vec<_> = catalog.techniques.iter().map(|t| &t.id).collect();
+        ids.sort();
+        ids.dedup();
+        assert_eq!(ids.len(), catalog.techniques.len());
+    }
+
+    #[test]
+    fn test_detected_by() {
+        let catalog = Catalog::bundled();
+        let ids = |detection| {
+            catalog
+                .detected_by(detection)
+                .iter()
+                .map(|t| t.id.as_str())
+                .collect::<Vec<_>>()
+        };
+        assert_eq!(ids("ld-so-preload"), ["T1574.006", "T1014"]);
+        assert_eq!(ids("secrets/aws-access-key-id"), ["T1552.001"]);
+        assert_eq!(
+            ids("secrets/private-key"),
+            ["T1552.001", "T1552.004", "T1021.004"]
+        );
+        assert!(ids("secrets").is_empty());
+        assert!(ids("no-such-check").is_empty());
+    }
+
+    #[test]
+    fn test_select() {
+        let catalog = Catalog::bundled();
+        let selected = catalog
+            .select(&["command-control".to_string(), "T1053".to_string()])
+            .unwrap();
+        let ids: Vec<_> = selected
+            .iter()
+            .map(|(ta, te)| (*ta, te.id.as_str()))
+            .collect();
+        assert_eq!(
+            ids,
+            [
+                ("persistence", "T1053.003"),
+                ("command-and-control", "T1071.001"),
+                ("command-and-control", "T1105"),
+                ("command-and-control", "T1573"),
+            ]
+        );
+        assert!(catalog.select(&["T9999".to_string()]).is_err());
+        assert_eq!(catalog.select(&[]).unwrap().len(), catalog.techniques.len());
+    }
+}
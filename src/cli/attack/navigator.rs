@@ -0,0 +1,194 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! ATT&CK Navigator layers
+//!
+//! Layers are the JSON files the Navigator opens to color a matrix. Each
+//! technique guestctl found evidence of gets the score of its most severe
+//! finding and a comment listing the findings.
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::path::Path;
+
+pub const LAYER_VERSION: &str = "4.5";
+pub const NAVIGATOR_VERSION: &str = "5.1.0";
+
+/// Findings listed in a technique's comment before the rest are counted
+const MAX_COMMENT_LINES: usize = 20;
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Layer {
+    pub name: String,
+    pub versions: Versions,
+    pub domain: String,
+    pub description: String,
+    pub techniques: Vec<LayerTechnique>,
+    pub gradient: Gradient,
+    pub legend_items: Vec<LegendItem>,
+    pub hide_disabled: bool,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Versions {
+    pub attack: String,
+    pub navigator: String,
+    pub layer: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct LayerTechnique {
+    #[serde(rename = "techniqueID")]
+    pub technique_id: String,
+    pub score: u32,
+    pub comment: String,
+    pub enabled: bool,
+    /// Set on parents so their scored sub-techniques are shown
+    pub show_subtechniques: bool,
+    #[serde(skip)]
+    findings: Vec<String>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Gradient {
+    pub colors: Vec<String>,
+    pub min_value: u32,
+    pub max_value: u32,
+}
+
+#[derive(Debug, Serialize)]
+pub struct LegendItem {
+    pub label: String,
+    pub color: String,
+}
+
+/// Layer score of a CRITICAL/HIGH/MEDIUM/LOW severity
+pub fn severity_score(severity: &str) -> u32 {
+    match severity.to_ascii_uppercase().as_str() {
+        "CRITICAL" => 4,
+        "HIGH" => 3,
+        "MEDIUM" => 2,
+        _ => 1,
+    }
+}
+
+impl Layer {
+    /// An empty Enterprise layer for an ATT&CK release such as `16.1`
+    pub fn new(name: &str, description: &str, attack_version: &str) -> Self {
+        let colors = ["#ffffff", "#ffe766", "#ffaf66", "#ff6666", "#b30000"];
+        Self {
+            name: name.to_string(),
+            versions: Versions {
+                attack: attack_version
+                    .split('.')
+                    .next()
+                    .unwrap_or(attack_version)
+                    .to_string(),
+                navigator: NAVIGATOR_VERSION.to_string(),
+                layer: LAYER_VERSION.to_string(),
+            },
+            domain: "enterprise-attack".to_string(),
+            description: description.to_string(),
+            techniques: Vec::new(),
+            gradient: Gradient {
+                colors: colors.iter().map(|c| c.to_string()).collect(),
+                min_value: 0,
+                max_value: 4,
+            },
+            legend_items: ["Low", "Medium", "High", "Critical"]
+                .iter()
+                .zip(&colors[1..])
+                .map(|(label, color)| LegendItem {
+                    label: label.to_string(),
+                    color: color.to_string(),
+                })
+                .collect(),
+            hide_disabled: false,
+        }
+    }
+
+    /// Record a finding against a technique, keeping the highest score
+    pub fn add(&mut self, technique_id: &str, score: u32, finding: &str) {
+        if let Some((parent, _)) = technique_id.split_once('.') {
+            self.entry(parent).show_subtechniques = true;
+        }
+        let entry = self.entry(technique_id);
+        entry.score = entry.score.max(score);
+        if !entry.findings.iter().any(|f| f == finding) {
+            entry.findings.push(finding.to_string());
+        }
+
+        let mut comment: Vec<&str> = entry
+            .findings
+            .iter()
+            .take(MAX_COMMENT_LINES)
+            .map(|f| f.as_str())
+            .collect();
+        let more = entry.findings.len().saturating_sub(MAX_COMMENT_LINES);
+        let tail = format!("... and {} more", more);
+        if more > 0 {
+            comment.push(&tail);
+        }
+        entry.comment = comment.join("\n");
+    }
+
+    /// Techniques with a finding
+    pub fn scored(&self) -> impl Iterator<Item = &LayerTechnique> {
+        self.techniques.iter().filter(|t| t.score > 0)
+    }
+
+    pub fn save(&self, path: &Path) -> Result<()> {
+        let json = serde_json::to_string_pretty(self)?;
+        std::fs::write(path, json).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    fn entry(&mut self, technique_id: &str) -> &mut LayerTechnique {
+        let index = match self
+            .techniques
+            .iter()
+            .position(|t| t.technique_id == technique_id)
+        {
+            Some(index) => index,
+            None => {
+                self.techniques.push(LayerTechnique {
+                    technique_id: technique_id.to_string(),
+                    score: 0,
+                    comment: String::new(),
+                    enabled: true,
+                    show_subtechniques: false,
+                    findings: Vec::new(),
+                });
+                self.techniques.len() - 1
+            }
+        };
+        &mut self.techniques[index]
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_layer() {
+        let mut layer = Layer::new("web01", "malware findings", "16.1");
+        layer.add("T1053.003", 1, "user-crontab: /var/spool/cron/crontabs/www");
+        layer.add("T1053.003", 3, "cron-persistence: /etc/cron.d/x");
+        layer.add("T1053.003", 3, "cron-persistence: /etc/cron.d/x");
+
+        let json = serde_json::to_value(&layer).unwrap();
+        assert_eq!(json["versions"]["attack"], "16");
+        assert_eq!(json["domain"], "enterprise-attack");
+        let techniques = json["techniques"].as_array().unwrap();
+        assert_eq!(techniques.len(), 2);
+        assert_eq!(techniques[0]["techniqueID"], "T1053");
+        assert_eq!(techniques[0]["showSubtechniques"], true);
+        assert_eq!(techniques[1]["score"], 3);
+        assert_eq!(
+            techniques[1]["comment"],
+            "user-crontab: /var/spool/cron/crontabs/www\ncron-persistence: /etc/cron.d/x"
+        );
+        assert_eq!(layer.scored().count(), 1);
+    }
+}
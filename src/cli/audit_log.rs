@@ -0,0 +1,183 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Audit log command and recording of mutating operations
+
+use crate::cli::plain;
+use anyhow::{Context, Result};
+use clap::{Args, Subcommand};
+use colored::*;
+use guestkit::core::audit::image_digest;
+use guestkit::core::{AuditActor, AuditLog, AuditRecord, AuditResult, AuditStatus};
+use serde_json::Value;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+/// Run a mutating operation on `image` and record it in the audit log
+///
+/// The image digest is taken before the operation runs. Failing to write
+/// the log does not fail the operation; it is reported on stderr.
+pub fn audited<T>(
+    operation: &str,
+    image: &Path,
+    parameters: Value,
+    run: impl FnOnce() -> Result<T>,
+) -> Result<T> {
+    audited_with(operation, image, parameters, run, |_| {
+        AuditResult::success()
+    })
+}
+
+/// Like [`audited`], with `summarize` deciding the result of operations
+/// that report failures in their return value
+pub fn audited_with<T>(
+    operation: &str,
+    image: &Path,
+    parameters: Value,
+    run: impl FnOnce() -> Result<T>,
+    summarize: impl FnOnce(&T) -> AuditResult,
+) -> Result<T> {
+    let mut record = AuditRecord::new(operation, AuditActor::current())
+        .with_image(image.display().to_string())
+        .with_parameters(parameters);
+    match image_digest(image) {
+        Ok(digest) => record = record.with_image_digest(digest),
+        Err(e) => eprintln!("{} Could not hash {}: {}", plain::text("⚠").yellow(), image.display(), e),
+    }
+
+    let outcome = run();
+    let result = match outcome {
+        Ok(ref value) => summarize(value),
+        Err(ref e) => AuditResult::failure(format!("{:#}", e)),
+    };
+
+    let log = AuditLog::new(AuditLog::default_path());
+    if let Err(e) = log.append(record, result) {
+        eprintln!(
+            "{} Failed to write audit log {}: {}",
+            plain::text("⚠").yellow(),
+            log.path().display(),
+            e
+        );
+    }
+
+    outcome
+}
+
+#[derive(Debug, Args)]
+pub struct AuditLogCommand {
+    /// Audit log file (default: $GUESTCTL_AUDIT_LOG or the user data directory)
+    #[arg(long, global = true, value_name = "FILE")]
+    pub log: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub action: AuditLogAction,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum AuditLogAction {
+    /// List recorded operations
+    Show {
+        /// Show only the last N entries
+        #[arg(short = 'n', long)]
+        limit: Option<usize>,
+    },
+
+    /// Check the hash chain for edited or removed entries
+    Verify,
+
+    /// Export all entries as a JSON array
+    Export {
+        /// Output file (default: stdout)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+}
+
+impl AuditLogCommand {
+    pub fn execute(&self) -> Result<()> {
+        let log = AuditLog::new(self.log.clone().unwrap_or_else(AuditLog::default_path));
+
+        match &self.action {
+            AuditLogAction::Show { limit } => show(&log, *limit),
+            AuditLogAction::Verify => verify(&log),
+            AuditLogAction::Export { output } => export(&log, output.as_deref()),
+        }
+    }
+}
+
+fn show(log: &AuditLog, limit: Option<usize>) -> Result<()> {
+    let entries = log
+        .entries()
+        .with_context(|| format!("Failed to read audit log: {}", log.path().display()))?;
+    if entries.is_empty() {
+        println!("No operations recorded in {}", log.path().display());
+        return Ok(());
+    }
+
+    let skip = limit.map_or(0, |limit| entries.len().saturating_sub(limit));
+    for entry in &entries[skip..] {
+        let status = match entry.result.status {
+            AuditStatus::Success => "success".green(),
+            AuditStatus::Failure => "failure".red(),
+            AuditStatus::Cancelled => "cancelled".yellow(),
+        };
+        println!(
+            "{:>5} {} {:<12} {:<9} {} {}",
+            entry.sequence.to_string().bright_black(),
+            entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+            entry.operation.bold(),
+            status,
+            entry.actor.user.cyan(),
+            entry.image.as_deref().unwrap_or("-").bright_blue()
+        );
+        if let Some(ref message) = entry.result.message {
+            println!("      {}", message.bright_black());
+        }
+    }
+
+    Ok(())
+}
+
+fn verify(log: &AuditLog) -> Result<()> {
+    let verification = log
+        .verify()
+        .with_context(|| format!("Failed to read audit log: {}", log.path().display()))?;
+
+    if verification.is_valid() {
+        println!(
+            "{} {} entries, hash chain intact",
+            plain::text("✓").green().bold(),
+            verification.entries
+        );
+        println!("  Head: {}", verification.head.bright_black());
+        return Ok(());
+    }
+
+    println!(
+        "{} Hash chain broken at line {}: {}",
+        plain::text("✗").red().bold(),
+        verification.broken_at.unwrap_or_default(),
+        verification.reason.as_deref().unwrap_or("unknown")
+    );
+    println!("  {} entries before it verify", verification.entries);
+    anyhow::bail!("Audit log {} has been modified", log.path().display());
+}
+
+fn export(log: &AuditLog, output: Option<&Path>) -> Result<()> {
+    let json = log
+        .export_json()
+        .with_context(|| format!("Failed to read audit log: {}", log.path().display()))?;
+
+    match output {
+        Some(path) => {
+            fs::write(path, json).with_context(|| format!("Failed to write {}", path.display()))?;
+            println!(
+                "{} Exported to: {}",
+                plain::text("✓").green(),
+                path.display().to_string().bright_blue()
+            );
+        }
+        None => println!("{}", json),
+    }
+
+    Ok(())
+}
@@ -2,6 +2,8 @@
 //! Batch script execution for guestctl CLI
 
 use super::errors::errors;
+use super::plain;
+use super::target;
 use anyhow::{Context, Result};
 use guestkit::Guestfs;
 use owo_colors::OwoColorize;
@@ -46,20 +48,22 @@ impl BatchExecutor {
 
         // Add drive
         if verbose {
-            println!("  {} Loading disk: {}", "→".truecolor(222, 115, 86), disk_path.display());
+            println!("  {} Loading disk: {}", plain::text("→").truecolor(222, 115, 86), disk_path.display());
         }
         handle
             .add_drive_ro(disk_path.to_str().unwrap())
             .context("Failed to add drive")?;
+        target::add_disks(&mut handle, true)?;
+        target::select(&mut handle);
 
         // Launch
         if verbose {
-            println!("  {} Launching appliance...", "→".truecolor(222, 115, 86));
+            println!("  {} Launching appliance...", plain::text("→").truecolor(222, 115, 86));
         }
         handle.launch().context("Failed to launch guestfs")?;
 
         // Auto-inspect
-        let roots = handle.inspect_os().unwrap_or_default();
+        let roots = target::os_roots(&mut handle)?;
         let current_root = roots.first().cloned();
 
         if verbose && current_root.is_some() {
@@ -68,7 +72,7 @@ impl BatchExecutor {
                     if let Ok(distro) = handle.inspect_get_distro(root) {
                         println!(
                             "  {} Detected: {} {}",
-                            "✓".green(),
+                            plain::text("✓").green(),
                             os_type.truecolor(222, 115, 86),
                             distro.truecolor(222, 115, 86)
                         );
@@ -116,7 +120,7 @@ impl BatchExecutor {
                 Ok(()) => {
                     report.successful_commands += 1;
                     if self.verbose {
-                        println!("  {}", "✓ Success".green());
+                        println!("  {}", plain::text("✓ Success").green());
                     }
                 }
                 Err(e) => {
@@ -127,7 +131,7 @@ impl BatchExecutor {
                         error: e.to_string(),
                     });
 
-                    eprintln!("  {} {}", "✗ Error:".red(), e.to_string().red());
+                    eprintln!("  {} {}", plain::text("✗ Error:").red(), e.to_string().red());
 
                     if self.fail_fast {
                         return Err(anyhow::anyhow!(
@@ -335,7 +339,7 @@ impl BatchExecutor {
                 RedirectMode::Write => "Wrote",
                 RedirectMode::Append => "Appended",
             };
-            println!("  {} {} output to {}", "→".truecolor(222, 115, 86), mode_str, redirect.path);
+            println!("  {} {} output to {}", plain::text("→").truecolor(222, 115, 86), mode_str, redirect.path);
         }
 
         Ok(())
@@ -380,7 +384,7 @@ impl ExecutionReport {
             for error in &self.errors {
                 println!(
                     "  {} Line {}: {}",
-                    "✗".red(),
+                    plain::text("✗").red(),
                     error.line_number,
                     error.command.dimmed()
                 );
@@ -391,11 +395,11 @@ impl ExecutionReport {
         println!("\n{}", "=".repeat(60).dimmed());
 
         if self.failed_commands == 0 {
-            println!("{}", "✓ All commands executed successfully!".green().bold());
+            println!("{}", plain::text("✓ All commands executed successfully!").green().bold());
         } else {
             println!(
                 "{}",
-                format!("⚠ {} command(s) failed", self.failed_commands)
+                plain::text(&format!("⚠ {} command(s) failed", self.failed_commands))
                     .yellow()
                     .bold()
             );
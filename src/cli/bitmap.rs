@@ -0,0 +1,191 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Bitmap command - qcow2 dirty bitmaps for incremental backups
+
+use crate::cli::audit_log::audited;
+use crate::cli::catalog::parse_image_ref;
+use crate::cli::output::{format_size, parse_size};
+use crate::cli::plain;
+use anyhow::{Context, Result};
+use clap::{Args, Subcommand};
+use colored::*;
+use guestkit::disk::qcow2::{Qcow2Image, DEFAULT_GRANULARITY};
+use serde_json::json;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Args)]
+pub struct BitmapCommand {
+    #[command(subcommand)]
+    pub action: BitmapAction,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum BitmapAction {
+    /// List the dirty bitmaps of a qcow2 image
+    List {
+        /// qcow2 image
+        #[arg(value_parser = parse_image_ref)]
+        image: PathBuf,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Export the blocks changed since a bitmap was created or cleared, as JSON
+    Export {
+        /// qcow2 image
+        #[arg(value_parser = parse_image_ref)]
+        image: PathBuf,
+
+        /// Bitmap name
+        bitmap: String,
+
+        /// Output file (default: stdout)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+    },
+
+    /// Add an empty bitmap that records guest writes from now on
+    Create {
+        /// qcow2 image
+        #[arg(value_parser = parse_image_ref)]
+        image: PathBuf,
+
+        /// Bitmap name
+        bitmap: String,
+
+        /// Bytes of guest disk per bit (e.g. 64K)
+        #[arg(long, value_parser = parse_size, default_value_t = DEFAULT_GRANULARITY)]
+        granularity: u64,
+    },
+
+    /// Mark every block of a bitmap clean, starting the next increment
+    Clear {
+        /// qcow2 image
+        #[arg(value_parser = parse_image_ref)]
+        image: PathBuf,
+
+        /// Bitmap name
+        bitmap: String,
+    },
+}
+
+impl BitmapCommand {
+    pub fn execute(&self) -> Result<()> {
+        match &self.action {
+            BitmapAction::List { image, format } => list(image, format),
+            BitmapAction::Export {
+                image,
+                bitmap,
+                output,
+            } => export(image, bitmap, output.as_deref()),
+            BitmapAction::Create {
+                image,
+                bitmap,
+                granularity,
+            } => create(image, bitmap, *granularity),
+            BitmapAction::Clear { image, bitmap } => clear(image, bitmap),
+        }
+    }
+}
+
+fn list(image: &Path, format: &str) -> Result<()> {
+    let bitmaps = Qcow2Image::open(image)
+        .and_then(|qcow2| qcow2.bitmaps())
+        .with_context(|| format!("Failed to read bitmaps of {}", image.display()))?;
+
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&bitmaps)?);
+        return Ok(());
+    }
+    if bitmaps.is_empty() {
+        println!("No bitmaps in {}", image.display());
+        return Ok(());
+    }
+
+    println!(
+        "{:<24} {:>12}  {}",
+        "Name".bold(),
+        "Granularity".bold(),
+        "Flags".bold()
+    );
+    for bitmap in &bitmaps {
+        let mut flags = Vec::new();
+        if bitmap.auto {
+            flags.push("auto");
+        }
+        if bitmap.in_use {
+            flags.push("in-use");
+        }
+        let flags = flags.join(",");
+        println!(
+            "{:<24} {:>12}  {}",
+            bitmap.name,
+            format_size(bitmap.granularity),
+            if bitmap.in_use {
+                flags.yellow()
+            } else {
+                flags.normal()
+            }
+        );
+    }
+    Ok(())
+}
+
+fn export(image: &Path, bitmap: &str, output: Option<&Path>) -> Result<()> {
+    let changes = Qcow2Image::open(image)
+        .and_then(|qcow2| qcow2.changed_blocks(bitmap))
+        .with_context(|| {
+            format!(
+                "Failed to export bitmap '{}' of {}",
+                bitmap,
+                image.display()
+            )
+        })?;
+    let json = serde_json::to_string_pretty(&changes)?;
+
+    match output {
+        Some(path) => {
+            fs::write(path, json).with_context(|| format!("Failed to write {}", path.display()))?;
+            eprintln!(
+                "{} {} changed in {} extents, exported to {}",
+                plain::text("✓").green(),
+                format_size(changes.changed_bytes),
+                changes.extents.len(),
+                path.display().to_string().bright_blue()
+            );
+        }
+        None => println!("{}", json),
+    }
+    Ok(())
+}
+
+fn create(image: &Path, bitmap: &str, granularity: u64) -> Result<()> {
+    let parameters = json!({ "bitmap": bitmap, "granularity": granularity });
+    let info = audited("bitmap-create", image, parameters, || {
+        Qcow2Image::open_rw(image)
+            .and_then(|mut qcow2| qcow2.add_bitmap(bitmap, granularity))
+            .with_context(|| format!("Failed to add bitmap '{}' to {}", bitmap, image.display()))
+    })?;
+
+    println!(
+        "{} Added bitmap {} ({} granularity)",
+        plain::text("✓").green(),
+        info.name.bold(),
+        format_size(info.granularity)
+    );
+    Ok(())
+}
+
+fn clear(image: &Path, bitmap: &str) -> Result<()> {
+    let parameters = json!({ "bitmap": bitmap });
+    audited("bitmap-clear", image, parameters, || {
+        Qcow2Image::open_rw(image)
+            .and_then(|mut qcow2| qcow2.clear_bitmap(bitmap))
+            .with_context(|| format!("Failed to clear bitmap '{}' of {}", bitmap, image.display()))
+    })?;
+
+    println!("{} Cleared bitmap {}", plain::text("✓").green(), bitmap.bold());
+    Ok(())
+}
@@ -6,6 +6,8 @@ pub mod ansible;
 pub mod kubernetes;
 pub mod compose;
 
+use crate::cli::plain;
+use crate::cli::target;
 use anyhow::Result;
 use guestkit::Guestfs;
 use serde::{Deserialize, Serialize};
@@ -96,12 +98,14 @@ pub fn analyze_image<P: AsRef<Path>>(image_path: P, verbose: bool) -> Result<Ima
     let image_path_str = image_path.as_ref().display().to_string();
 
     if verbose {
-        println!("📋 Analyzing image for blueprint: {}", image_path_str);
+        println!("{}Analyzing image for blueprint: {}", plain::text("📋 "), image_path_str);
     }
 
     // Initialize guestfs
     let mut g = Guestfs::new()?;
     g.add_drive_opts(&image_path, true, None)?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
     g.launch()?;
 
     // Inspect OS
@@ -113,10 +117,7 @@ pub fn analyze_image<P: AsRef<Path>>(image_path: P, verbose: bool) -> Result<Ima
     let root = &roots[0];
 
     // Mount filesystems
-    let mountpoints = g.inspect_get_mountpoints(root)?;
-    for (mp, dev) in mountpoints {
-        let _ = g.mount(&dev, &mp);
-    }
+    g.mount_os_ro(root)?;
 
     // Get OS information
     let os_name = g.inspect_get_product_name(root).unwrap_or_else(|_| "Unknown".to_string());
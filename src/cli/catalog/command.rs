@@ -0,0 +1,270 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Catalog command - manage the local image catalog
+
+use super::{Catalog, CatalogEntry};
+use crate::cli::plain;
+use anyhow::{Context, Result};
+use clap::{Args, Subcommand};
+use colored::*;
+use guestkit::core::audit::image_digest;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Args)]
+pub struct CatalogCommand {
+    /// Catalog file (default: $GUESTCTL_CATALOG or the user data directory)
+    #[arg(long, global = true, value_name = "FILE")]
+    pub catalog: Option<PathBuf>,
+
+    #[command(subcommand)]
+    pub action: CatalogAction,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum CatalogAction {
+    /// Add an image to the catalog
+    Add {
+        /// Image path or URI
+        location: String,
+
+        /// Image name (default: file name without extension)
+        #[arg(short, long)]
+        name: Option<String>,
+
+        /// Tag to attach (repeatable)
+        #[arg(short, long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+
+        /// Owner (default: current user)
+        #[arg(long)]
+        owner: Option<String>,
+
+        /// Skip computing the SHA-256 digest of local images
+        #[arg(long)]
+        no_digest: bool,
+    },
+
+    /// List cataloged images
+    List {
+        /// Only images carrying this tag (repeatable, all must match)
+        #[arg(short, long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Show one image
+    Show {
+        /// Image reference (name, name:tag or id)
+        reference: String,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Remove an image from the catalog (the image file is kept)
+    Remove {
+        /// Image reference (name, name:tag or id)
+        reference: String,
+    },
+}
+
+impl CatalogCommand {
+    pub fn execute(&self) -> Result<()> {
+        let mut catalog =
+            Catalog::load(self.catalog.clone().unwrap_or_else(Catalog::default_path))?;
+
+        match &self.action {
+            CatalogAction::Add {
+                location,
+                name,
+                tags,
+                owner,
+                no_digest,
+            } => add(
+                &mut catalog,
+                location,
+                name.as_deref(),
+                tags,
+                owner.as_deref(),
+                *no_digest,
+            ),
+            CatalogAction::List { tags, format } => list(&catalog, tags, format),
+            CatalogAction::Show { reference, format } => show(&catalog, reference, format),
+            CatalogAction::Remove { reference } => remove(&mut catalog, reference),
+        }
+    }
+}
+
+fn add(
+    catalog: &mut Catalog,
+    location: &str,
+    name: Option<&str>,
+    tags: &[String],
+    owner: Option<&str>,
+    no_digest: bool,
+) -> Result<()> {
+    let path = Path::new(location);
+    let local = path.exists();
+    if !local && !location.contains("://") {
+        anyhow::bail!("Image not found: {}", location);
+    }
+
+    let location = if local {
+        std::fs::canonicalize(path)
+            .with_context(|| format!("Failed to resolve {}", path.display()))?
+            .display()
+            .to_string()
+    } else {
+        location.to_string()
+    };
+    if let Some(existing) = catalog.find_by_location(Path::new(&location)) {
+        anyhow::bail!(
+            "{} is already cataloged as {} ({})",
+            location,
+            existing.reference(),
+            existing.id
+        );
+    }
+    let name = match name {
+        Some(name) => name.to_string(),
+        None => default_name(&location)?,
+    };
+
+    let mut entry = CatalogEntry::new(name, &location).with_tags(tags.to_vec());
+    if let Some(owner) = owner
+        .map(str::to_string)
+        .or_else(|| std::env::var("USER").ok())
+    {
+        entry = entry.with_owner(owner);
+    }
+    if local && !no_digest {
+        println!("{} Computing digest of {}...", plain::text("→").cyan(), location);
+        let digest = image_digest(Path::new(&location))
+            .with_context(|| format!("Failed to hash {}", location))?;
+        entry = entry.with_digest(digest);
+    }
+
+    let entry = catalog.add(entry)?.clone();
+    catalog.save()?;
+
+    println!(
+        "{} Added {} ({})",
+        plain::text("✓").green(),
+        entry.reference().bold(),
+        entry.id.bright_black()
+    );
+    Ok(())
+}
+
+fn list(catalog: &Catalog, tags: &[String], format: &str) -> Result<()> {
+    let entries = catalog.select(tags);
+
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&entries)?);
+        return Ok(());
+    }
+
+    if entries.is_empty() {
+        println!("No images in {}", catalog.path().display());
+        return Ok(());
+    }
+
+    println!(
+        "{:<12}  {:<24}  {:<16}  {:<28}  {}",
+        "ID".bold(),
+        "NAME".bold(),
+        "TAGS".bold(),
+        "OS".bold(),
+        "LOCATION".bold()
+    );
+    for entry in entries {
+        println!(
+            "{:<12}  {:<24}  {:<16}  {:<28}  {}",
+            entry.id.bright_black(),
+            entry.name,
+            entry.tags.join(","),
+            entry
+                .inspection
+                .as_ref()
+                .map(|i| i.describe())
+                .unwrap_or_else(|| "-".to_string()),
+            entry.location.bright_blue()
+        );
+    }
+    Ok(())
+}
+
+fn show(catalog: &Catalog, reference: &str, format: &str) -> Result<()> {
+    let entry = catalog.require(reference)?;
+
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(entry)?);
+        return Ok(());
+    }
+
+    println!("{}", entry.reference().bold());
+    println!("  ID:       {}", entry.id);
+    println!("  Location: {}", entry.location.bright_blue());
+    println!(
+        "  Tags:     {}",
+        if entry.tags.is_empty() {
+            "-".to_string()
+        } else {
+            entry.tags.join(", ")
+        }
+    );
+    println!("  Owner:    {}", entry.owner.as_deref().unwrap_or("-"));
+    println!("  Digest:   {}", entry.digest.as_deref().unwrap_or("-"));
+    println!(
+        "  Added:    {}",
+        entry.added_at.format("%Y-%m-%d %H:%M:%S UTC")
+    );
+
+    match &entry.inspection {
+        Some(inspection) => {
+            println!(
+                "  Inspected: {}",
+                inspection.inspected_at.format("%Y-%m-%d %H:%M:%S UTC")
+            );
+            println!("    OS:       {}", inspection.describe());
+            if let Some(hostname) = &inspection.hostname {
+                println!("    Hostname: {}", hostname);
+            }
+            if let Some(packages) = inspection.packages {
+                println!("    Packages: {}", packages);
+            }
+        }
+        None => println!(
+            "  Inspected: {} (run guestctl inspect {})",
+            "never".yellow(),
+            entry.reference()
+        ),
+    }
+    Ok(())
+}
+
+fn remove(catalog: &mut Catalog, reference: &str) -> Result<()> {
+    let entry = catalog.remove(reference)?;
+    catalog.save()?;
+
+    println!(
+        "{} Removed {} ({})",
+        plain::text("✓").green(),
+        entry.reference().bold(),
+        entry.location
+    );
+    Ok(())
+}
+
+/// Image name from a location: the file name without its extension
+fn default_name(location: &str) -> Result<String> {
+    let file = location.rsplit('/').next().unwrap_or(location);
+    let stem = file.split('.').next().unwrap_or(file);
+    if stem.is_empty() {
+        anyhow::bail!("Cannot derive a name from {}; use --name", location);
+    }
+    Ok(stem.to_string())
+}
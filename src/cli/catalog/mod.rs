@@ -0,0 +1,543 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Image catalog: local registry of known images
+//!
+//! The catalog maps names and tags to image locations, so commands accept
+//! `name:tag` references in place of paths, batch runs can select images by
+//! tag and fleet reports identify images by their catalog id.
+//!
+//! References:
+//! - `name:tag` - the entry called `name` carrying `tag`
+//! - `name` - the only entry called `name`, or the one tagged `latest`
+//! - `<id>` - the entry with that catalog id
+
+pub mod command;
+
+pub use command::CatalogCommand;
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use crate::cli::formatters::InspectionReport;
+
+/// Environment variable overriding the catalog location
+pub const CATALOG_ENV: &str = "GUESTCTL_CATALOG";
+
+/// Tag used when a bare name matches several entries
+pub const DEFAULT_TAG: &str = "latest";
+
+/// Outcome of the last inspection of a cataloged image
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct InspectionSummary {
+    pub inspected_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os_type: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distribution: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub product_name: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub architecture: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hostname: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub packages: Option<usize>,
+}
+
+impl InspectionSummary {
+    /// Summarize an inspection report
+    pub fn from_report(report: &InspectionReport) -> Self {
+        let os = &report.os;
+        Self {
+            inspected_at: Utc::now(),
+            os_type: os.os_type.clone(),
+            distribution: os.distribution.clone(),
+            product_name: os.product_name.clone(),
+            version: os
+                .version
+                .as_ref()
+                .map(|v| format!("{}.{}", v.major, v.minor)),
+            architecture: os.architecture.clone(),
+            hostname: os.hostname.clone(),
+            packages: report.packages.as_ref().map(|p| p.count),
+        }
+    }
+
+    /// One-line description, e.g. "Ubuntu 22.04.3 LTS (x86_64)"
+    pub fn describe(&self) -> String {
+        let name = self
+            .product_name
+            .clone()
+            .or_else(|| {
+                self.distribution
+                    .as_ref()
+                    .map(|d| format!("{} {}", d, self.version.as_deref().unwrap_or("")))
+            })
+            .or_else(|| self.os_type.clone())
+            .unwrap_or_else(|| "unknown".to_string());
+        match &self.architecture {
+            Some(arch) => format!("{} ({})", name.trim(), arch),
+            None => name.trim().to_string(),
+        }
+    }
+}
+
+/// One image in the catalog
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct CatalogEntry {
+    /// Stable identity of the image
+    pub id: String,
+    pub name: String,
+    /// Local path or URI
+    pub location: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub digest: Option<String>,
+    #[serde(default)]
+    pub tags: Vec<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub owner: Option<String>,
+    pub added_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub inspection: Option<InspectionSummary>,
+}
+
+impl CatalogEntry {
+    /// New entry with a fresh id
+    pub fn new(name: impl Into<String>, location: impl Into<String>) -> Self {
+        let id = uuid::Uuid::new_v4().simple().to_string();
+        Self {
+            id: id[..12].to_string(),
+            name: name.into(),
+            location: location.into(),
+            digest: None,
+            tags: Vec::new(),
+            owner: None,
+            added_at: Utc::now(),
+            inspection: None,
+        }
+    }
+
+    pub fn with_tags(mut self, tags: Vec<String>) -> Self {
+        self.tags = tags;
+        self
+    }
+
+    pub fn with_owner(mut self, owner: impl Into<String>) -> Self {
+        self.owner = Some(owner.into());
+        self
+    }
+
+    pub fn with_digest(mut self, digest: impl Into<String>) -> Self {
+        self.digest = Some(digest.into());
+        self
+    }
+
+    /// Preferred reference: `name:tag` with the first tag, else the name
+    pub fn reference(&self) -> String {
+        match self.tags.first() {
+            Some(tag) => format!("{}:{}", self.name, tag),
+            None => self.name.clone(),
+        }
+    }
+
+    pub fn has_tag(&self, tag: &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
+
+    /// Whether the entry points at `path`
+    fn is_at(&self, path: &Path) -> bool {
+        let location = Path::new(&self.location);
+        location == path
+            || matches!(
+                (fs::canonicalize(location), fs::canonicalize(path)),
+                (Ok(a), Ok(b)) if a == b
+            )
+    }
+}
+
+/// Catalog database (a JSON file)
+#[derive(Debug, Default)]
+pub struct Catalog {
+    path: PathBuf,
+    entries: Vec<CatalogEntry>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct CatalogFile {
+    version: u32,
+    images: Vec<CatalogEntry>,
+}
+
+impl Catalog {
+    /// Default location: `$GUESTCTL_CATALOG`, else `catalog.json` in the
+    /// user data directory
+    pub fn default_path() -> PathBuf {
+        if let Some(path) = std::env::var_os(CATALOG_ENV) {
+            return PathBuf::from(path);
+        }
+
+        dirs::data_local_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("guestctl")
+            .join("catalog.json")
+    }
+
+    /// Load the catalog at `path`; a missing file is an empty catalog
+    pub fn load(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        if !path.exists() {
+            return Ok(Self {
+                path,
+                entries: Vec::new(),
+            });
+        }
+
+        let content = fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read catalog: {}", path.display()))?;
+        let file: CatalogFile = serde_json::from_str(&content)
+            .with_context(|| format!("Failed to parse catalog: {}", path.display()))?;
+        Ok(Self {
+            path,
+            entries: file.images,
+        })
+    }
+
+    /// Load the catalog at the default location
+    pub fn load_default() -> Result<Self> {
+        Self::load(Self::default_path())
+    }
+
+    /// Write the catalog back, replacing the file atomically
+    pub fn save(&self) -> Result<()> {
+        if let Some(parent) = self.path.parent() {
+            fs::create_dir_all(parent)
+                .with_context(|| format!("Failed to create {}", parent.display()))?;
+        }
+
+        let file = CatalogFile {
+            version: 1,
+            images: self.entries.clone(),
+        };
+        let tmp = self.path.with_extension("json.tmp");
+        fs::write(&tmp, serde_json::to_string_pretty(&file)?)
+            .with_context(|| format!("Failed to write {}", tmp.display()))?;
+        fs::rename(&tmp, &self.path)
+            .with_context(|| format!("Failed to write catalog: {}", self.path.display()))?;
+        Ok(())
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Add an entry
+    ///
+    /// Fails if the name or a tag is malformed, if one of its `name:tag`
+    /// references is taken, or if the location is already cataloged.
+    pub fn add(&mut self, entry: CatalogEntry) -> Result<&CatalogEntry> {
+        validate_name(&entry.name)?;
+        for tag in &entry.tags {
+            validate_tag(tag)?;
+        }
+
+        for existing in &self.entries {
+            if existing.is_at(Path::new(&entry.location)) {
+                anyhow::bail!(
+                    "{} is already cataloged as {} ({})",
+                    entry.location,
+                    existing.reference(),
+                    existing.id
+                );
+            }
+            if existing.name == entry.name {
+                if let Some(tag) = entry.tags.iter().find(|t| existing.has_tag(t)) {
+                    anyhow::bail!(
+                        "{}:{} already refers to {}",
+                        entry.name,
+                        tag,
+                        existing.location
+                    );
+                }
+                if entry.tags.is_empty() && existing.tags.is_empty() {
+                    anyhow::bail!(
+                        "{} already refers to {}; add a tag to tell them apart",
+                        entry.name,
+                        existing.location
+                    );
+                }
+            }
+        }
+
+        self.entries.push(entry);
+        Ok(self.entries.last().expect("entry just added"))
+    }
+
+    /// Remove the entry `reference` resolves to
+    pub fn remove(&mut self, reference: &str) -> Result<CatalogEntry> {
+        let id = self.require(reference)?.id.clone();
+        let index = self
+            .entries
+            .iter()
+            .position(|e| e.id == id)
+            .expect("resolved entry is in the catalog");
+        Ok(self.entries.remove(index))
+    }
+
+    /// Resolve a reference
+    ///
+    /// Returns `None` when nothing in the catalog is called that, and an
+    /// error when the name exists but the reference is ambiguous or names a
+    /// tag it does not carry.
+    pub fn resolve(&self, reference: &str) -> Result<Option<&CatalogEntry>> {
+        if let Some(entry) = self.entries.iter().find(|e| e.id == reference) {
+            return Ok(Some(entry));
+        }
+
+        let (name, tag) = match reference.split_once(':') {
+            Some((name, tag)) => (name, Some(tag)),
+            None => (reference, None),
+        };
+        let named: Vec<&CatalogEntry> = self.entries.iter().filter(|e| e.name == name).collect();
+        if named.is_empty() {
+            return Ok(None);
+        }
+
+        match tag {
+            Some(tag) => named
+                .into_iter()
+                .find(|e| e.has_tag(tag))
+                .map(Some)
+                .ok_or_else(|| anyhow::anyhow!("Image {} has no tag {}", name, tag)),
+            None if named.len() == 1 => Ok(Some(named[0])),
+            None => named
+                .iter()
+                .find(|e| e.has_tag(DEFAULT_TAG))
+                .copied()
+                .map(Some)
+                .ok_or_else(|| {
+                    let refs: Vec<String> = named.iter().map(|e| e.reference()).collect();
+                    anyhow::anyhow!(
+                        "Image name {} is ambiguous, use one of: {}",
+                        name,
+                        refs.join(", ")
+                    )
+                }),
+        }
+    }
+
+    /// Resolve a reference that must exist
+    pub fn require(&self, reference: &str) -> Result<&CatalogEntry> {
+        self.resolve(reference)?.ok_or_else(|| {
+            anyhow::anyhow!("No image {} in catalog {}", reference, self.path.display())
+        })
+    }
+
+    /// Entries carrying all of `tags`
+    pub fn select(&self, tags: &[String]) -> Vec<&CatalogEntry> {
+        self.entries
+            .iter()
+            .filter(|e| tags.iter().all(|tag| e.has_tag(tag)))
+            .collect()
+    }
+
+    /// Entry cataloged at `path`
+    pub fn find_by_location(&self, path: &Path) -> Option<&CatalogEntry> {
+        self.entries.iter().find(|e| e.is_at(path))
+    }
+
+    /// Store the inspection summary of the entry at `path`; false if the
+    /// image is not cataloged
+    pub fn record_inspection(&mut self, path: &Path, summary: InspectionSummary) -> bool {
+        match self.entries.iter_mut().find(|e| e.is_at(path)) {
+            Some(entry) => {
+                entry.inspection = Some(summary);
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+/// Clap value parser for image arguments: existing paths are kept as they
+/// are, `oci://` references are pulled, anything else is looked up in the
+/// catalog
+pub fn parse_image_ref(value: &str) -> std::result::Result<PathBuf, String> {
+    let path = PathBuf::from(value);
+    if path.exists() {
+        return Ok(path);
+    }
+
+    #[cfg(feature = "oci")]
+    if let Some(reference) = value.strip_prefix(crate::cli::oci::OCI_SCHEME) {
+        return crate::cli::oci::pull_cached(reference).map_err(|e| format!("{:#}", e));
+    }
+
+    let Ok(catalog) = Catalog::load_default() else {
+        return Ok(path);
+    };
+    match catalog.resolve(value) {
+        Ok(Some(entry)) => Ok(PathBuf::from(&entry.location)),
+        Ok(None) => Ok(path),
+        Err(e) => Err(e.to_string()),
+    }
+}
+
+/// Update the last inspection of `image` in the default catalog
+///
+/// Images that are not cataloged are ignored; failures are only logged, so
+/// inspection never fails because of the catalog.
+pub fn record_inspection(image: &Path, report: &InspectionReport) {
+    // Batch inspection records from several threads
+    static UPDATE: Mutex<()> = Mutex::new(());
+    let _guard = UPDATE.lock().unwrap_or_else(|e| e.into_inner());
+
+    let result = Catalog::load_default().and_then(|mut catalog| {
+        if catalog.record_inspection(image, InspectionSummary::from_report(report)) {
+            catalog.save()?;
+        }
+        Ok(())
+    });
+    if let Err(e) = result {
+        log::debug!("Could not update catalog for {}: {:#}", image.display(), e);
+    }
+}
+
+fn validate_name(name: &str) -> Result<()> {
+    if name.is_empty()
+        || !name
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-' | '/'))
+    {
+        anyhow::bail!(
+            "Invalid image name '{}': use letters, digits, '.', '_', '-' and '/'",
+            name
+        );
+    }
+    Ok(())
+}
+
+fn validate_tag(tag: &str) -> Result<()> {
+    if tag.is_empty()
+        || !tag
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '_' | '-'))
+    {
+        anyhow::bail!(
+            "Invalid tag '{}': use letters, digits, '.', '_' and '-'",
+            tag
+        );
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, location: &str, tags: &[&str]) -> CatalogEntry {
+        CatalogEntry::new(name, location).with_tags(tags.iter().map(|t| t.to_string()).collect())
+    }
+
+    fn catalog() -> Catalog {
+        let mut catalog = Catalog::default();
+        catalog
+            .add(entry("web", "/images/web-1.qcow2", &["v1", "prod"]))
+            .unwrap();
+        catalog
+            .add(entry("web", "/images/web-2.qcow2", &["v2", "latest"]))
+            .unwrap();
+        catalog
+            .add(entry("db", "/images/db.qcow2", &["prod"]))
+            .unwrap();
+        catalog
+    }
+
+    #[test]
+    fn test_resolve_references() {
+        let catalog = catalog();
+        let location = |r: &str| catalog.resolve(r).unwrap().map(|e| e.location.as_str());
+
+        assert_eq!(location("web:prod"), Some("/images/web-1.qcow2"));
+        assert_eq!(location("web"), Some("/images/web-2.qcow2"));
+        assert_eq!(location("db"), Some("/images/db.qcow2"));
+        assert_eq!(location("mail"), None);
+        assert!(catalog.resolve("web:v3").is_err());
+
+        let id = catalog.entries[2].id.clone();
+        assert_eq!(location(&id), Some("/images/db.qcow2"));
+    }
+
+    #[test]
+    fn test_ambiguous_name() {
+        let mut catalog = Catalog::default();
+        catalog.add(entry("app", "/a.img", &["v1"])).unwrap();
+        catalog.add(entry("app", "/b.img", &["v2"])).unwrap();
+        let error = catalog.resolve("app").unwrap_err().to_string();
+        assert!(error.contains("app:v1"));
+        assert!(error.contains("app:v2"));
+    }
+
+    #[test]
+    fn test_add_rejects_conflicts() {
+        let mut catalog = catalog();
+        assert!(catalog
+            .add(entry("web", "/images/web-3.qcow2", &["v1"]))
+            .is_err());
+        assert!(catalog
+            .add(entry("other", "/images/db.qcow2", &[]))
+            .is_err());
+        assert!(catalog.add(entry("bad name", "/x.img", &[])).is_err());
+        assert!(catalog.add(entry("x", "/x.img", &["a:b"])).is_err());
+        assert!(catalog
+            .add(entry("web", "/images/web-3.qcow2", &["v3"]))
+            .is_ok());
+    }
+
+    #[test]
+    fn test_select_and_remove() {
+        let mut catalog = catalog();
+        let prod: Vec<&str> = catalog
+            .select(&["prod".to_string()])
+            .iter()
+            .map(|e| e.name.as_str())
+            .collect();
+        assert_eq!(prod, vec!["web", "db"]);
+        assert_eq!(catalog.select(&[]).len(), 3);
+
+        let removed = catalog.remove("web:v1").unwrap();
+        assert_eq!(removed.location, "/images/web-1.qcow2");
+        assert_eq!(catalog.entries.len(), 2);
+        assert!(catalog.remove("web:v1").is_err());
+    }
+
+    #[test]
+    fn test_save_and_load() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("catalog.json");
+
+        let mut catalog = Catalog::load(&path).unwrap();
+        assert!(catalog.entries.is_empty());
+        catalog
+            .add(entry("web", "/images/web.qcow2", &["prod"]).with_owner("alice"))
+            .unwrap();
+        assert!(catalog.record_inspection(
+            Path::new("/images/web.qcow2"),
+            InspectionSummary {
+                distribution: Some("ubuntu".to_string()),
+                version: Some("22.4".to_string()),
+                ..Default::default()
+            }
+        ));
+        catalog.save().unwrap();
+
+        let loaded = Catalog::load(&path).unwrap();
+        assert_eq!(loaded.entries, catalog.entries);
+        let inspection = loaded.entries[0].inspection.as_ref().unwrap();
+        assert_eq!(inspection.describe(), "ubuntu 22.4");
+    }
+}
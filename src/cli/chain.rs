@@ -0,0 +1,144 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Chain and rebase commands - inspect and repoint qcow2 backing chains
+
+use crate::cli::audit_log::audited;
+use crate::cli::catalog::parse_image_ref;
+use crate::cli::output::format_size;
+use crate::cli::plain;
+use anyhow::{Context, Result};
+use clap::Args;
+use colored::*;
+use guestkit::converters::DiskConverter;
+use serde_json::json;
+use std::path::PathBuf;
+
+#[derive(Debug, Args)]
+pub struct ChainCommand {
+    /// Disk image
+    #[arg(value_parser = parse_image_ref)]
+    pub image: PathBuf,
+
+    /// Output format (text, json)
+    #[arg(short, long, default_value = "text")]
+    pub format: String,
+}
+
+impl ChainCommand {
+    pub fn execute(&self) -> Result<()> {
+        let chain = DiskConverter::new()
+            .backing_chain(&self.image)
+            .with_context(|| format!("Failed to read backing chain of {}", self.image.display()))?;
+
+        if self.format == "json" {
+            println!("{}", serde_json::to_string_pretty(&chain)?);
+            return Ok(());
+        }
+
+        println!(
+            "{:<5} {:<7} {:>10} {:>10}  {}",
+            "Depth".bold(),
+            "Format".bold(),
+            "Virtual".bold(),
+            "On disk".bold(),
+            "File".bold()
+        );
+        for (depth, layer) in chain.iter().enumerate() {
+            println!(
+                "{:<5} {:<7} {:>10} {:>10}  {}",
+                depth,
+                layer.format,
+                format_size(layer.virtual_size),
+                layer
+                    .actual_size
+                    .map(format_size)
+                    .unwrap_or_else(|| "-".to_string()),
+                layer.filename
+            );
+        }
+
+        let on_disk: u64 = chain.iter().filter_map(|layer| layer.actual_size).sum();
+        println!(
+            "\n{} images, {} on disk",
+            chain.len(),
+            format_size(on_disk).bold()
+        );
+        Ok(())
+    }
+}
+
+#[derive(Debug, Args)]
+pub struct RebaseCommand {
+    /// Image whose backing file to change
+    #[arg(value_parser = parse_image_ref)]
+    pub image: PathBuf,
+
+    /// New backing file, relative paths are taken from the image's directory
+    #[arg(
+        long,
+        value_name = "FILE",
+        required_unless_present = "remove_backing",
+        conflicts_with = "remove_backing"
+    )]
+    pub new_backing: Option<PathBuf>,
+
+    /// Drop the backing file, copying its data into the image (standalone image)
+    #[arg(long)]
+    pub remove_backing: bool,
+
+    /// Format of the new backing file (default: probed)
+    #[arg(short = 'F', long, value_name = "FORMAT")]
+    pub backing_format: Option<String>,
+
+    /// Only rewrite the backing file name, without comparing or copying data
+    ///
+    /// Use this after moving or renaming a backing file whose content is
+    /// unchanged. If the content differs, the guest sees corrupted data.
+    #[arg(long = "unsafe")]
+    pub unsafe_mode: bool,
+}
+
+impl RebaseCommand {
+    pub fn execute(&self) -> Result<()> {
+        let image = &self.image;
+        let new_backing = self.new_backing.as_deref();
+
+        if self.unsafe_mode && self.remove_backing {
+            println!(
+                "{} --unsafe with --remove-backing leaves the image without the data it read from its backing file",
+                plain::text("⚠").yellow()
+            );
+        }
+
+        let parameters = json!({
+            "new_backing": new_backing,
+            "backing_format": self.backing_format,
+            "unsafe": self.unsafe_mode,
+        });
+        audited("rebase", image, parameters, || {
+            DiskConverter::new()
+                .rebase(
+                    image,
+                    new_backing,
+                    self.backing_format.as_deref(),
+                    self.unsafe_mode,
+                )
+                .with_context(|| format!("Failed to rebase {}", image.display()))
+        })?;
+
+        match new_backing {
+            Some(backing) => println!(
+                "{} {} now backed by {}{}",
+                plain::text("✓").green(),
+                image.display(),
+                backing.display().to_string().bright_blue(),
+                if self.unsafe_mode { " (unsafe)" } else { "" }
+            ),
+            None => println!(
+                "{} {} no longer has a backing file",
+                plain::text("✓").green(),
+                image.display()
+            ),
+        }
+        Ok(())
+    }
+}
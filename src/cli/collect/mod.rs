@@ -0,0 +1,406 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Forensic triage collection
+//!
+//! `collect --profile forensic` copies the files of the profile's
+//! [`targets`] out of the guest unmodified, hashes each with MD5, SHA-1
+//! and SHA-256, and packs them with a manifest into a `.tar.gz`. The
+//! archive's own SHA-256 is written next to it.
+
+pub mod reporter;
+pub mod targets;
+
+use crate::cli::plain;
+use crate::cli::target;
+use anyhow::{Context, Result};
+use guestkit::Guestfs;
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use targets::Target;
+
+/// Files larger than this are listed as skipped rather than copied
+const MAX_FILE_BYTES: i64 = 512 * 1024 * 1024;
+
+/// Collection profiles
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Profile {
+    /// Every target, for incident response
+    Forensic,
+}
+
+impl Profile {
+    pub fn from_name(name: &str) -> Option<Self> {
+        match name.to_ascii_lowercase().as_str() {
+            "forensic" => Some(Self::Forensic),
+            _ => None,
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Forensic => "forensic",
+        }
+    }
+
+    pub fn targets(&self) -> Vec<&'static Target> {
+        match self {
+            Self::Forensic => targets::TARGETS.iter().collect(),
+        }
+    }
+}
+
+/// A file copied into the package
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Artifact {
+    pub target: String,
+    /// Source path in the guest
+    pub guest_path: String,
+    /// Path relative to the package root
+    pub archive_path: String,
+    pub size_bytes: u64,
+    /// Permission bits in octal, e.g. `0600`
+    pub mode: String,
+    pub uid: u32,
+    pub gid: u32,
+    /// Guest timestamps, RFC 3339
+    pub mtime: String,
+    pub atime: String,
+    pub ctime: String,
+    pub md5: String,
+    pub sha1: String,
+    pub sha256: String,
+}
+
+/// A file of a target that could not be collected
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedArtifact {
+    pub target: String,
+    pub guest_path: String,
+    pub reason: String,
+}
+
+/// Package manifest, stored as `manifest.json` at the package root
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Manifest {
+    /// `guestctl <version>`
+    pub collector: String,
+    pub image_path: String,
+    pub profile: Profile,
+    pub targets: Vec<String>,
+    pub started_at: String,
+    pub completed_at: String,
+    pub os_name: Option<String>,
+    pub hostname: Option<String>,
+    pub artifacts: Vec<Artifact>,
+    pub skipped: Vec<SkippedArtifact>,
+    /// Host path of the compressed package
+    pub archive: String,
+    /// SHA-256 of the compressed package, also written to `<archive>.sha256`
+    pub archive_sha256: Option<String>,
+}
+
+impl Manifest {
+    pub fn total_bytes(&self) -> u64 {
+        self.artifacts.iter().map(|a| a.size_bytes).sum()
+    }
+
+    /// Number of artifacts collected for a target
+    pub fn count(&self, target: &str) -> usize {
+        self.artifacts.iter().filter(|a| a.target == target).count()
+    }
+}
+
+/// Collect the given targets of a disk image into `archive` (.tar.gz)
+pub fn create_package<P: AsRef<Path>>(
+    image_path: P,
+    archive: &Path,
+    profile: Profile,
+    targets: &[&Target],
+    verbose: bool,
+) -> Result<Manifest> {
+    let started_at = chrono::Utc::now().to_rfc3339();
+    let image_path_str = image_path.as_ref().display().to_string();
+
+    if verbose {
+        println!(
+            "{} Collecting {} triage package from: {}",
+            plain::text("🗃️ "),
+            profile.as_str(),
+            image_path_str
+        );
+    }
+
+    // Initialize guestfs
+    let mut g = Guestfs::new()?;
+    g.add_drive_opts(&image_path, true, None)?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
+    g.launch()?;
+
+    // Inspect OS
+    let roots = g.inspect_os()?;
+    if roots.is_empty() {
+        anyhow::bail!("No operating systems found in disk image");
+    }
+
+    let root = &roots[0];
+    let os_name = g.inspect_get_product_name(root).ok();
+    let hostname = g.inspect_get_hostname(root).ok();
+
+    // Mount filesystems
+    g.mount_os_ro(root)?;
+
+    // Stage files in a temporary directory named after the archive
+    let staging = tempfile::tempdir()?;
+    let package_name = package_name(archive);
+    let package_root = staging.path().join(&package_name);
+    std::fs::create_dir_all(&package_root)?;
+
+    let homes = targets::homes(&mut g);
+    let mut artifacts: Vec<Artifact> = Vec::new();
+    let mut skipped = Vec::new();
+    for target in targets {
+        for source in target.sources {
+            for path in targets::expand(&mut g, *source, &homes) {
+                // Targets overlap; the first one to name a file keeps it
+                if artifacts.iter().any(|a| a.guest_path == path) {
+                    continue;
+                }
+                if verbose {
+                    println!("  [{}] {}", target.name, path);
+                }
+                match copy_artifact(&mut g, &package_root, target.name, &path) {
+                    Ok(artifact) => artifacts.push(artifact),
+                    Err(reason) => skipped.push(SkippedArtifact {
+                        target: target.name.to_string(),
+                        guest_path: path,
+                        reason,
+                    }),
+                }
+            }
+        }
+    }
+
+    // Shutdown guestfs
+    g.shutdown()?;
+
+    let mut manifest = Manifest {
+        collector: format!("guestctl {}", guestkit::VERSION),
+        image_path: image_path_str,
+        profile,
+        targets: targets.iter().map(|t| t.name.to_string()).collect(),
+        started_at,
+        completed_at: chrono::Utc::now().to_rfc3339(),
+        os_name,
+        hostname,
+        artifacts,
+        skipped,
+        archive: archive.display().to_string(),
+        archive_sha256: None,
+    };
+
+    std::fs::write(
+        package_root.join("manifest.json"),
+        serde_json::to_string_pretty(&manifest)?,
+    )?;
+    std::fs::write(
+        package_root.join("manifest.txt"),
+        reporter::format_report(&manifest),
+    )?;
+    std::fs::write(package_root.join("SHA256SUMS"), sha256sums(&manifest))?;
+
+    // Compress the staged package
+    let output = Command::new("tar")
+        .arg("-czf")
+        .arg(archive)
+        .arg("-C")
+        .arg(staging.path())
+        .arg(&package_name)
+        .output()
+        .context("Failed to run tar")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to create triage archive: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    let file = std::fs::File::open(archive)
+        .with_context(|| format!("Failed to read {}", archive.display()))?;
+    let digest = hash(file)?.2;
+    let archive_name = archive
+        .file_name()
+        .map(|n| n.to_string_lossy().to_string())
+        .unwrap_or_default();
+    std::fs::write(
+        checksum_path(archive),
+        format!("{}  {}\n", digest, archive_name),
+    )?;
+    manifest.archive_sha256 = Some(digest);
+
+    Ok(manifest)
+}
+
+/// Copy one guest file into the package and hash it
+fn copy_artifact(
+    g: &mut Guestfs,
+    package_root: &Path,
+    target: &str,
+    guest_path: &str,
+) -> Result<Artifact, String> {
+    let stat = g.lstat(guest_path).map_err(|e| e.to_string())?;
+    if stat.size > MAX_FILE_BYTES {
+        return Err(format!("file too large ({} bytes)", stat.size));
+    }
+
+    let archive_path = archive_path(guest_path);
+    let host_path = package_root.join(&archive_path);
+    if let Some(parent) = host_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    g.download(guest_path, &host_path.to_string_lossy())
+        .map_err(|e| e.to_string())?;
+
+    let file = std::fs::File::open(&host_path).map_err(|e| e.to_string())?;
+    let size_bytes = file.metadata().map_err(|e| e.to_string())?.len();
+    let (md5, sha1, sha256) = hash(file).map_err(|e| e.to_string())?;
+
+    Ok(Artifact {
+        target: target.to_string(),
+        guest_path: guest_path.to_string(),
+        archive_path,
+        size_bytes,
+        mode: format!("{:04o}", stat.mode & 0o7777),
+        uid: stat.uid,
+        gid: stat.gid,
+        mtime: timestamp(stat.mtime),
+        atime: timestamp(stat.atime),
+        ctime: timestamp(stat.ctime),
+        md5,
+        sha1,
+        sha256,
+    })
+}
+
+/// MD5, SHA-1 and SHA-256 of a stream, in hex
+fn hash(mut reader: impl Read) -> std::io::Result<(String, String, String)> {
+    let mut md5 = Md5::new();
+    let mut sha1 = Sha1::new();
+    let mut sha256 = Sha256::new();
+    let mut buf = vec![0u8; 64 * 1024];
+    loop {
+        let n = reader.read(&mut buf)?;
+        if n == 0 {
+            break;
+        }
+        md5.update(&buf[..n]);
+        sha1.update(&buf[..n]);
+        sha256.update(&buf[..n]);
+    }
+    Ok((
+        hex(&md5.finalize()),
+        hex(&sha1.finalize()),
+        hex(&sha256.finalize()),
+    ))
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+fn timestamp(secs: i64) -> String {
+    chrono::DateTime::from_timestamp(secs, 0)
+        .map(|t| t.to_rfc3339())
+        .unwrap_or_default()
+}
+
+/// `sha256sum -c` input for the collected files
+fn sha256sums(manifest: &Manifest) -> String {
+    manifest
+        .artifacts
+        .iter()
+        .map(|a| format!("{}  {}\n", a.sha256, a.archive_path))
+        .collect()
+}
+
+/// Location of a guest file inside the package, e.g. `files/var/log/wtmp`
+fn archive_path(guest_path: &str) -> String {
+    let relative: Vec<&str> = guest_path
+        .split('/')
+        .filter(|part| !part.is_empty() && *part != "." && *part != "..")
+        .collect();
+    format!("files/{}", relative.join("/"))
+}
+
+/// Default archive path for an image: `<image-stem>-triage.tar.gz`
+pub fn default_archive_path(image: &Path) -> PathBuf {
+    let stem = image
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "guest".to_string());
+    PathBuf::from(format!("{}-triage.tar.gz", stem))
+}
+
+/// Path of the archive checksum file: `<archive>.sha256`
+pub fn checksum_path(archive: &Path) -> PathBuf {
+    let mut path = archive.as_os_str().to_owned();
+    path.push(".sha256");
+    PathBuf::from(path)
+}
+
+/// Top-level directory name inside the archive
+fn package_name(archive: &Path) -> String {
+    let name = archive
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "triage".to_string());
+
+    name.trim_end_matches(".tar.gz")
+        .trim_end_matches(".tgz")
+        .to_string()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_archive_path() {
+        assert_eq!(archive_path("/var/log/wtmp"), "files/var/log/wtmp");
+        assert_eq!(
+            archive_path("/home/../../root/.bash_history"),
+            "files/home/root/.bash_history"
+        );
+    }
+
+    #[test]
+    fn test_hash() {
+        let (md5, sha1, sha256) = hash(&b"abc"[..]).unwrap();
+        assert_eq!(md5, "900150983cd24fb0d6963f7d28e17f72");
+        assert_eq!(sha1, "a9993e364706816aba3e25717850c26c9cd0d89d");
+        assert_eq!(
+            sha256,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+    }
+
+    #[test]
+    fn test_paths() {
+        let archive = default_archive_path(Path::new("/images/web01.qcow2"));
+        assert_eq!(archive, PathBuf::from("web01-triage.tar.gz"));
+        assert_eq!(package_name(&archive), "web01-triage");
+        assert_eq!(
+            checksum_path(&archive),
+            PathBuf::from("web01-triage.tar.gz.sha256")
+        );
+        assert_eq!(Profile::from_name("Forensic"), Some(Profile::Forensic));
+        assert_eq!(Profile::from_name("support"), None);
+    }
+}
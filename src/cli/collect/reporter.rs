@@ -0,0 +1,74 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Triage package manifest formatting
+
+use super::{targets, Manifest};
+
+/// Format the manifest as text
+pub fn format_report(manifest: &Manifest) -> String {
+    let mut output = String::new();
+
+    output.push_str("🗃️  Triage Package\n");
+    output.push_str("==================\n\n");
+    output.push_str(&format!("Image: {}\n", manifest.image_path));
+    if let Some(ref os) = manifest.os_name {
+        output.push_str(&format!("OS: {}\n", os));
+    }
+    if let Some(ref hostname) = manifest.hostname {
+        output.push_str(&format!("Hostname: {}\n", hostname));
+    }
+    output.push_str(&format!("Profile: {}\n", manifest.profile.as_str()));
+    output.push_str(&format!("Collector: {}\n", manifest.collector));
+    output.push_str(&format!("Started: {}\n", manifest.started_at));
+    output.push_str(&format!("Completed: {}\n", manifest.completed_at));
+    output.push_str(&format!("Archive: {}\n", manifest.archive));
+    if let Some(ref digest) = manifest.archive_sha256 {
+        output.push_str(&format!("Archive SHA-256: {}\n", digest));
+    }
+    output.push('\n');
+
+    // Summary
+    output.push_str("📊 Targets\n");
+    output.push_str("----------\n");
+    for name in &manifest.targets {
+        let description = targets::target(name).map_or("", |t| t.description);
+        output.push_str(&format!(
+            "{:<18} {:>5} files  {}\n",
+            name,
+            manifest.count(name),
+            description
+        ));
+    }
+    output.push_str(&format!(
+        "Total: {} files, {:.2} MB, {} skipped\n\n",
+        manifest.artifacts.len(),
+        manifest.total_bytes() as f64 / 1_048_576.0,
+        manifest.skipped.len()
+    ));
+
+    // Contents
+    output.push_str("📁 Artifacts\n");
+    output.push_str("------------\n");
+    for artifact in &manifest.artifacts {
+        output.push_str(&format!(
+            "{}  {} {}:{} {}  {}\n",
+            artifact.sha256,
+            artifact.mode,
+            artifact.uid,
+            artifact.gid,
+            artifact.mtime,
+            artifact.guest_path
+        ));
+    }
+    output.push('\n');
+
+    if !manifest.skipped.is_empty() {
+        output.push_str("⚠️  Skipped\n");
+        output.push_str("----------\n");
+        for skipped in &manifest.skipped {
+            output.push_str(&format!("{}: {}\n", skipped.guest_path, skipped.reason));
+        }
+        output.push('\n');
+    }
+
+    output
+}
@@ -0,0 +1,284 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Collection targets
+//!
+//! A target is a named group of guest sources, in the spirit of KAPE
+//! targets. Sources under home directories are expanded for every home
+//! listed in `/etc/passwd`.
+
+use guestkit::Guestfs;
+
+/// Where a target's files come from
+#[derive(Debug, Clone, Copy)]
+pub enum Source {
+    /// One file
+    File(&'static str),
+    /// A log file and its rotations (`auth.log.1`, `secure-20240101.gz`, ...)
+    Rotated(&'static str),
+    /// Every file below a directory
+    Tree(&'static str),
+    /// A file or directory relative to each home directory
+    Home(&'static str),
+    /// Files with one of the given names below a directory relative to
+    /// each home directory
+    HomeNamed(&'static str, &'static [&'static str]),
+}
+
+/// A named group of sources
+#[derive(Debug)]
+pub struct Target {
+    pub name: &'static str,
+    pub description: &'static str,
+    pub sources: &'static [Source],
+}
+
+/// Browser databases holding history, cookies, downloads and saved logins
+const BROWSER_FILES: &[&str] = &[
+    "places.sqlite",
+    "places.sqlite-wal",
+    "cookies.sqlite",
+    "cookies.sqlite-wal",
+    "formhistory.sqlite",
+    "downloads.sqlite",
+    "logins.json",
+    "History",
+    "History-journal",
+    "Cookies",
+    "Cookies-journal",
+    "Login Data",
+    "Web Data",
+    "Preferences",
+];
+
+pub const TARGETS: &[Target] = &[
+    Target {
+        name: "auth-logs",
+        description: "Authentication, sudo and audit logs",
+        sources: &[
+            Source::Rotated("/var/log/auth.log"),
+            Source::Rotated("/var/log/secure"),
+            Source::File("/var/log/faillog"),
+            Source::File("/var/log/sudo.log"),
+            Source::Tree("/var/log/audit"),
+        ],
+    },
+    Target {
+        name: "login-records",
+        description: "utmp, wtmp, btmp and lastlog",
+        sources: &[
+            Source::Rotated("/var/log/wtmp"),
+            Source::Rotated("/var/log/btmp"),
+            Source::File("/var/log/lastlog"),
+            Source::File("/var/run/utmp"),
+            Source::File("/run/utmp"),
+        ],
+    },
+    Target {
+        name: "shell-history",
+        description: "Shell and interpreter histories",
+        sources: &[
+            Source::Home(".bash_history"),
+            Source::Home(".zsh_history"),
+            Source::Home(".ash_history"),
+            Source::Home(".sh_history"),
+            Source::Home(".history"),
+            Source::Home(".local/share/fish/fish_history"),
+            Source::Home(".python_history"),
+            Source::Home(".mysql_history"),
+            Source::Home(".psql_history"),
+            Source::Home(".lesshst"),
+            Source::Home(".viminfo"),
+            Source::Home(".wget-hsts"),
+        ],
+    },
+    Target {
+        name: "browser-profiles",
+        description: "Firefox and Chromium history, cookies and logins",
+        sources: &[
+            Source::HomeNamed(".mozilla/firefox", BROWSER_FILES),
+            Source::HomeNamed(".config/google-chrome", BROWSER_FILES),
+            Source::HomeNamed(".config/chromium", BROWSER_FILES),
+            Source::HomeNamed(".config/BraveSoftware/Brave-Browser", BROWSER_FILES),
+            Source::HomeNamed(".config/microsoft-edge", BROWSER_FILES),
+        ],
+    },
+    Target {
+        name: "accounts",
+        description: "Users, groups and sudo rules",
+        sources: &[
+            Source::File("/etc/passwd"),
+            Source::File("/etc/shadow"),
+            Source::File("/etc/group"),
+            Source::File("/etc/gshadow"),
+            Source::File("/etc/sudoers"),
+            Source::Tree("/etc/sudoers.d"),
+        ],
+    },
+    Target {
+        name: "ssh",
+        description: "SSH daemon config, authorized keys and known hosts",
+        sources: &[
+            Source::File("/etc/ssh/sshd_config"),
+            Source::Tree("/etc/ssh/sshd_config.d"),
+            Source::Home(".ssh/authorized_keys"),
+            Source::Home(".ssh/authorized_keys2"),
+            Source::Home(".ssh/known_hosts"),
+            Source::Home(".ssh/config"),
+        ],
+    },
+    Target {
+        name: "persistence",
+        description: "Cron, systemd units, preloads and login scripts",
+        sources: &[
+            Source::File("/etc/crontab"),
+            Source::Tree("/etc/cron.d"),
+            Source::Tree("/var/spool/cron"),
+            Source::Tree("/etc/systemd/system"),
+            Source::Tree("/usr/local/lib/systemd/system"),
+            Source::File("/etc/rc.local"),
+            Source::File("/etc/ld.so.preload"),
+            Source::Tree("/etc/profile.d"),
+            Source::File("/etc/bash.bashrc"),
+            Source::File("/etc/bashrc"),
+            Source::Home(".bashrc"),
+            Source::Home(".bash_profile"),
+            Source::Home(".profile"),
+            Source::Home(".config/autostart"),
+            Source::Home(".config/systemd/user"),
+        ],
+    },
+    Target {
+        name: "system-logs",
+        description: "Syslog, kernel log and the systemd journal",
+        sources: &[
+            Source::Rotated("/var/log/syslog"),
+            Source::Rotated("/var/log/messages"),
+            Source::Rotated("/var/log/kern.log"),
+            Source::Tree("/var/log/journal"),
+        ],
+    },
+    Target {
+        name: "network",
+        description: "Name resolution and firewall configuration",
+        sources: &[
+            Source::File("/etc/hosts"),
+            Source::File("/etc/resolv.conf"),
+            Source::File("/etc/hostname"),
+            Source::Tree("/etc/iptables"),
+            Source::File("/etc/nftables.conf"),
+            Source::Tree("/etc/firewalld/zones"),
+        ],
+    },
+];
+
+/// A target by name
+pub fn target(name: &str) -> Option<&'static Target> {
+    TARGETS.iter().find(|t| t.name.eq_ignore_ascii_case(name))
+}
+
+/// Whether `name` is `base` or one of its rotations
+pub fn is_rotation(base: &str, name: &str) -> bool {
+    let Some(rest) = name.strip_prefix(base) else {
+        return false;
+    };
+    let rest = rest.strip_suffix(".gz").unwrap_or(rest);
+    let rest = rest.strip_suffix(".xz").unwrap_or(rest);
+    match rest.chars().next() {
+        None => true,
+        Some('.') | Some('-') => {
+            let suffix = &rest[1..];
+            !suffix.is_empty() && suffix.chars().all(|c| c.is_ascii_digit())
+        }
+        _ => false,
+    }
+}
+
+/// Home directories of accounts in `/etc/passwd`, and `/root`
+pub fn homes(g: &mut Guestfs) -> Vec<String> {
+    let passwd = g.read_file("/etc/passwd").unwrap_or_default();
+    let mut homes = vec!["/root".to_string()];
+    for line in String::from_utf8_lossy(&passwd).lines() {
+        let Some(home) = line.split(':').nth(5) else {
+            continue;
+        };
+        if home.len() > 1 && !homes.iter().any(|h| h == home) && g.is_dir(home).unwrap_or(false) {
+            homes.push(home.to_string());
+        }
+    }
+    homes
+}
+
+/// Regular files of a guest file or directory
+fn files(g: &mut Guestfs, path: &str) -> Vec<String> {
+    if g.is_dir(path).unwrap_or(false) {
+        let mut files = g.find(path).unwrap_or_default();
+        files.sort();
+        files
+    } else if g.is_file(path).unwrap_or(false) {
+        vec![path.to_string()]
+    } else {
+        Vec::new()
+    }
+}
+
+/// Guest files a source stands for
+pub fn expand(g: &mut Guestfs, source: Source, homes: &[String]) -> Vec<String> {
+    match source {
+        Source::File(path) | Source::Tree(path) => files(g, path),
+        Source::Rotated(path) => {
+            let (dir, base) = path.rsplit_once('/').unwrap_or(("", path));
+            let dir = if dir.is_empty() { "/" } else { dir };
+            let mut names: Vec<String> = g
+                .ls(dir)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|name| is_rotation(base, name))
+                .collect();
+            names.sort();
+            names
+                .into_iter()
+                .map(|name| format!("{}/{}", dir.trim_end_matches('/'), name))
+                .filter(|path| g.is_file(path).unwrap_or(false))
+                .collect()
+        }
+        Source::Home(relative) => homes
+            .iter()
+            .flat_map(|home| files(g, &format!("{}/{}", home, relative)))
+            .collect(),
+        Source::HomeNamed(relative, names) => homes
+            .iter()
+            .flat_map(|home| files(g, &format!("{}/{}", home, relative)))
+            .filter(|path| names.contains(&path.rsplit('/').next().unwrap_or("")))
+            .collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_is_rotation() {
+        assert!(is_rotation("auth.log", "auth.log"));
+        assert!(is_rotation("auth.log", "auth.log.1"));
+        assert!(is_rotation("auth.log", "auth.log.2.gz"));
+        assert!(is_rotation("secure", "secure-20240107"));
+        assert!(is_rotation("wtmp", "wtmp.1"));
+        assert!(!is_rotation("wtmp", "wtmpx"));
+        assert!(!is_rotation("secure", "secure-backup"));
+        assert!(!is_rotation("auth.log", "auth.log."));
+    }
+
+    #[test]
+    fn test_target_names() {
+        assert_eq!(
+            target("SHELL-HISTORY").map(|t| t.name),
+            Some("shell-history")
+        );
+        assert!(target("registry").is_none());
+
+        let mut names: Vec<&str> = TARGETS.iter().map(|t| t.name).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), TARGETS.len());
+    }
+}
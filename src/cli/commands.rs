@@ -2,12 +2,25 @@
 //! CLI commands implementation
 
 use super::formatters::*;
+use super::i18n;
+use super::inventory::cve::{self, CveLookup};
+use super::inventory::VulnerabilityInfo;
+use super::output::{pad_display, truncate_display};
+use super::plain;
+use super::preview;
 use super::profiles::{FindingStatus, ProfileReport};
+use super::target;
+use super::vulndb::vex::{self, Statement, Vex, VexDocument};
+use super::vulndb::VulnDb;
 use anyhow::{Context, Result};
 use guestkit::core::systemd::boot::BootAnalyzer;
+use guestkit::core::systemd::budget::{BootBudget, BudgetReport};
 use guestkit::core::systemd::journal::{JournalFilter, JournalReader};
+use guestkit::core::systemd::journal_file;
 use guestkit::core::systemd::services::ServiceAnalyzer;
 use guestkit::core::{ProgressReporter, SystemdAnalyzer};
+use guestkit::guestfs::luks::LuksToken;
+use guestkit::guestfs::{Application, LuksBinding, LvType, SysprepOperation};
 use guestkit::Guestfs;
 use owo_colors::OwoColorize;
 use std::path::{Path, PathBuf};
@@ -21,6 +34,7 @@ fn collect_inspection_data(
 ) -> Result<InspectionReport> {
     let mut report = InspectionReport {
         image_path: None,
+        os_instances: None,
         os: OsInfo {
             root: root.to_string(),
             os_type: g.inspect_get_type(root).ok(),
@@ -136,11 +150,18 @@ fn collect_inspection_data(
                     .collect()
             });
 
-            if lvm.is_some() || swap_devices.is_some() || fstab_mounts.is_some() {
+            let encrypted_volumes = Some(collect_encrypted_volumes(g)).filter(|v| !v.is_empty());
+
+            if lvm.is_some()
+                || swap_devices.is_some()
+                || fstab_mounts.is_some()
+                || encrypted_volumes.is_some()
+            {
                 Some(StorageInfo {
                     lvm,
                     swap_devices,
                     fstab_mounts,
+                    encrypted_volumes,
                 })
             } else {
                 None
@@ -237,21 +258,27 @@ fn collect_inspection_data(
     // Windows-specific inspection
     if let Some(ref os_type) = report.os.os_type {
         if os_type == "windows" {
+            let system = g.inspect_windows_system(root).ok();
             let software = g.inspect_windows_software(root).ok();
             let services = g.inspect_windows_services(root).ok();
+            let scheduled_tasks = g.inspect_windows_scheduled_tasks(root).ok();
             let network_adapters = g.inspect_windows_network(root).ok();
             let updates = g.inspect_windows_updates(root).ok();
             let event_logs = g.inspect_windows_events(root, "System", 10).ok();
 
-            if software.is_some()
+            if system.is_some()
+                || software.is_some()
                 || services.is_some()
+                || scheduled_tasks.is_some()
                 || network_adapters.is_some()
                 || updates.is_some()
                 || event_logs.is_some()
             {
                 report.windows = Some(WindowsInfo {
+                    system,
                     software,
                     services,
+                    scheduled_tasks,
                     network_adapters,
                     updates,
                     event_logs,
@@ -264,12 +291,78 @@ fn collect_inspection_data(
 }
 
 /// Print profile report in text format
+/// LUKS volumes of the image with their key slot bindings
+fn collect_encrypted_volumes(g: &mut Guestfs) -> Vec<EncryptedVolume> {
+    let partitions = g.list_partitions().unwrap_or_default();
+    partitions
+        .into_iter()
+        .filter_map(|device| {
+            let luks = g.luks_detect(&device).ok().flatten()?;
+            Some(EncryptedVolume {
+                unlocked_as: g.luks_mapping(&device),
+                device,
+                luks,
+            })
+        })
+        .collect()
+}
+
+/// What a LUKS token binds to, e.g. "tang http://tang.example.com"
+fn describe_token(token: &LuksToken) -> String {
+    let mut parts = vec![token.binding.to_string()];
+    if !token.pins.is_empty() {
+        let pins: Vec<String> = token.pins.iter().map(|p| p.to_string()).collect();
+        parts.push(format!("({})", pins.join(", ")));
+    }
+    parts.extend(token.tang_urls.iter().cloned());
+    if let Some(pcrs) = &token.tpm2_pcrs {
+        parts.push(format!("PCRs {}", pcrs));
+    }
+    if token.binding == LuksBinding::Other {
+        parts.push(format!("[{}]", token.token_type));
+    }
+    parts.join(" ")
+}
+
+fn print_encrypted_volume(volume: &EncryptedVolume) {
+    let status = match &volume.unlocked_as {
+        Some(mapper) => format!("unlocked as {}", mapper).green().to_string(),
+        None => "locked".yellow().to_string(),
+    };
+    println!("  {} {} LUKS{} {}",
+        plain::text("🔒").truecolor(222, 115, 86),
+        volume.device.bright_white().bold(),
+        volume.luks.version,
+        status);
+    println!("    {} UUID:   {}", plain::text("•").bright_black(), volume.luks.uuid.bright_black());
+    if let Some(cipher) = &volume.luks.cipher {
+        println!("    {} Cipher: {}", plain::text("•").bright_black(), cipher.bright_white());
+    }
+
+    for slot in &volume.luks.keyslots {
+        let binding = volume
+            .luks
+            .tokens
+            .iter()
+            .find(|t| t.keyslots.contains(&slot.id))
+            .map(describe_token)
+            .unwrap_or_else(|| slot.binding.to_string());
+        println!("    {} Key slot {}: {}", plain::text("•").bright_black(), slot.id, binding.bright_white());
+    }
+
+    if volume.unlocked_as.is_none() {
+        println!("    {} Unlock with --key {}:prompt (or :file:PATH, :clevis)",
+            plain::text("💡").bright_black(),
+            volume.device);
+    }
+}
+
 fn print_profile_report(report: &ProfileReport) {
     println!("Profile: {}", report.profile_name);
     println!();
 
     for section in &report.sections {
-        println!("━━━ {} ━━━", section.title);
+        println!("{} {} {}", plain::text("━━━"), section.title, plain::text("━━━"));
         println!();
 
         for finding in &section.findings {
@@ -288,14 +381,14 @@ fn print_profile_report(report: &ProfileReport) {
 
             println!(
                 "  {} {}: {}{}",
-                status_symbol, finding.item, finding.message, risk_display
+                plain::text(status_symbol), finding.item, finding.message, risk_display
             );
         }
         println!();
     }
 
     if let Some(summary) = &report.summary {
-        println!("━━━ Summary ━━━");
+        println!("{} Summary {}", plain::text("━━━"), plain::text("━━━"));
         println!("{}", summary);
         println!();
     }
@@ -327,6 +420,7 @@ fn print_inspection_report(
 /// Inspect a disk image and display OS information
 pub fn inspect_image(
     image: &PathBuf,
+    extra_disks: &[PathBuf],
     verbose: bool,
     debug: bool,
     output_format: Option<OutputFormat>,
@@ -338,6 +432,9 @@ pub fn inspect_image(
 ) -> Result<()> {
     use super::cache::InspectionCache;
 
+    // Cached reports are keyed by a single image
+    let use_cache = use_cache && extra_disks.is_empty();
+
     // Try to get cached result if caching is enabled
     if use_cache && !force_refresh {
         if let Ok(cache) = InspectionCache::new() {
@@ -357,7 +454,7 @@ pub fn inspect_image(
 
                 // Handle profile output
                 if profile.is_some() {
-                    println!("⚠ Cannot use profiles with cached results. Use --cache-refresh to re-inspect.");
+                    println!("{} Cannot use profiles with cached results. Use --cache-refresh to re-inspect.", plain::text("⚠"));
                     return Ok(());
                 }
 
@@ -378,6 +475,13 @@ pub fn inspect_image(
         eprintln!("[VERBOSE] Adding drive: {}", image.display());
     }
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::select(&mut g);
+    for disk in extra_disks {
+        if verbose {
+            eprintln!("[VERBOSE] Adding drive: {}", disk.display());
+        }
+        g.add_drive_ro(disk)?;
+    }
 
     progress.set_message("Launching appliance...");
     if verbose {
@@ -391,8 +495,8 @@ pub fn inspect_image(
     if verbose {
         eprintln!("[VERBOSE] Enumerating block devices...");
     }
-    println!("\n{}", "💾 Block Devices".truecolor(222, 115, 86).bold());
-    println!("{}", "─".repeat(60).bright_black());
+    println!("\n{}", plain::text("💾 Block Devices").truecolor(222, 115, 86).bold());
+    println!("{}", plain::text("─").repeat(60).bright_black());
     let devices = g.list_devices()?;
     for device in &devices {
         let size = g.blockdev_getsize64(device)?;
@@ -400,7 +504,7 @@ pub fn inspect_image(
             eprintln!("[VERBOSE] Found device: {} ({} bytes)", device, size);
         }
         println!("  {} {} {} ({:.2} GB)",
-            "▪".truecolor(222, 115, 86),
+            plain::text("▪").truecolor(222, 115, 86),
             device.bright_white().bold(),
             format!("{} bytes", size).bright_black(),
             size as f64 / 1e9);
@@ -408,13 +512,13 @@ pub fn inspect_image(
         // Additional device information
         if let Ok(ro) = g.blockdev_getro(device) {
             if ro {
-                println!("    {} Read-only: {}", "•".bright_black(), "yes".red());
+                println!("    {} Read-only: {}", plain::text("•").bright_black(), "yes".red());
             } else {
-                println!("    {} Read-only: {}", "•".bright_black(), "no".green());
+                println!("    {} Read-only: {}", plain::text("•").bright_black(), "no".green());
             }
         }
         if let Ok(ss) = g.blockdev_getss(device) {
-            println!("    {} Sector size: {}", "•".bright_black(), format!("{} bytes", ss).bright_white());
+            println!("    {} Sector size: {}", plain::text("•").bright_black(), format!("{} bytes", ss).bright_white());
         }
     }
 
@@ -422,27 +526,27 @@ pub fn inspect_image(
     if verbose {
         eprintln!("[VERBOSE] Analyzing partition table...");
     }
-    println!("\n{}", "🗂  Partitions".truecolor(222, 115, 86).bold());
-    println!("{}", "─".repeat(60).bright_black());
+    println!("\n{}", plain::text("🗂  Partitions").truecolor(222, 115, 86).bold());
+    println!("{}", plain::text("─").repeat(60).bright_black());
     let partitions = g.list_partitions()?;
     for partition in &partitions {
         if verbose {
             eprintln!("[VERBOSE] Examining partition: {}", partition);
         }
-        println!("  {} {}", "📦".truecolor(222, 115, 86), partition.bright_white().bold());
+        println!("  {} {}", plain::text("📦").truecolor(222, 115, 86), partition.bright_white().bold());
 
         if let Ok(part_list) = g.part_list("/dev/sda") {
             let part_num = g.part_to_partnum(partition)?;
             if let Some(p) = part_list.iter().find(|p| p.part_num == part_num) {
-                println!("    {} Number: {}", "•".bright_black(), format!("{}", p.part_num).yellow());
-                println!("    {} Start:  {}", "•".bright_black(), format!("{} bytes", p.part_start).bright_black());
+                println!("    {} Number: {}", plain::text("•").bright_black(), format!("{}", p.part_num).yellow());
+                println!("    {} Start:  {}", plain::text("•").bright_black(), format!("{} bytes", p.part_start).bright_black());
                 println!(
                     "    {} Size:   {} ({})",
-                    "•".bright_black(),
+                    plain::text("•").bright_black(),
                     format!("{} bytes", p.part_size).bright_black(),
                     format!("{:.2} GB", p.part_size as f64 / 1e9).bright_white()
                 );
-                println!("    {} End:    {}", "•".bright_black(), format!("{} bytes", p.part_end).bright_black());
+                println!("    {} End:    {}", plain::text("•").bright_black(), format!("{} bytes", p.part_end).bright_black());
             }
         }
     }
@@ -452,14 +556,14 @@ pub fn inspect_image(
         eprintln!("[VERBOSE] Detecting partition scheme...");
     }
     if let Ok(scheme) = g.part_get_parttype("/dev/sda") {
-        println!("\n{}", "⚙️  Partition Scheme".truecolor(222, 115, 86).bold());
-        println!("{}", "─".repeat(60).bright_black());
+        println!("\n{}", plain::text("⚙️  Partition Scheme").truecolor(222, 115, 86).bold());
+        println!("{}", plain::text("─").repeat(60).bright_black());
         let scheme_icon = match scheme.as_str() {
             "gpt" => "🔷",
             "msdos" | "mbr" => "🔶",
             _ => "⬡",
         };
-        println!("  {} Type: {}", scheme_icon, scheme.bright_white().bold());
+        println!("  {} Type: {}", plain::text(scheme_icon), scheme.bright_white().bold());
         if verbose {
             eprintln!("[VERBOSE] Partition scheme: {}", scheme);
         }
@@ -469,8 +573,8 @@ pub fn inspect_image(
     if verbose {
         eprintln!("[VERBOSE] Detecting filesystems...");
     }
-    println!("\n{}", "📁 Filesystems".truecolor(222, 115, 86).bold());
-    println!("{}", "─".repeat(60).bright_black());
+    println!("\n{}", plain::text("📁 Filesystems").truecolor(222, 115, 86).bold());
+    println!("{}", plain::text("─").repeat(60).bright_black());
     let filesystems = g.list_filesystems()?;
     for (device, fstype) in &filesystems {
         if verbose {
@@ -484,29 +588,40 @@ pub fn inspect_image(
             "ntfs" => "🪟",
             "vfat" | "fat" => "📂",
             "swap" => "💾",
+            "crypto_LUKS" | "BitLocker" => "🔒",
             _ => "❓",
         };
 
         if fstype == "unknown" {
-            println!("  {} {} {}", fs_icon, device.yellow(), fstype.bright_black());
+            println!("  {} {} {}", plain::text(fs_icon), device.yellow(), fstype.bright_black());
         } else {
-            println!("  {} {} {}", fs_icon, device.yellow(), fstype.bright_white().bold());
+            println!("  {} {} {}", plain::text(fs_icon), device.yellow(), fstype.bright_white().bold());
         }
 
         if fstype != "unknown" && fstype != "swap" {
             if let Ok(label) = g.vfs_label(device) {
                 if !label.is_empty() {
-                    println!("    {} Label: {}", "•".bright_black(), label.bright_white());
+                    println!("    {} Label: {}", plain::text("•").bright_black(), label.bright_white());
                 }
             }
             if let Ok(uuid) = g.vfs_uuid(device) {
                 if !uuid.is_empty() {
-                    println!("    {} UUID:  {}", "•".bright_black(), uuid.bright_black());
+                    println!("    {} UUID:  {}", plain::text("•").bright_black(), uuid.bright_black());
                 }
             }
         }
     }
 
+    // Encrypted volumes
+    let encrypted_volumes = collect_encrypted_volumes(&mut g);
+    if !encrypted_volumes.is_empty() {
+        println!("\n{}", plain::text("🔐 Encrypted Volumes").truecolor(222, 115, 86).bold());
+        println!("{}", plain::text("─").repeat(60).bright_black());
+        for volume in &encrypted_volumes {
+            print_encrypted_volume(volume);
+        }
+    }
+
     // OS inspection
     progress.set_message("Detecting operating systems...");
     if verbose {
@@ -563,12 +678,27 @@ pub fn inspect_image(
             return Ok(());
         }
 
-        // Collect data for first root (or all roots if needed)
+        // Collect data for the first root (the one chosen with --os-root)
         let mut report = collect_inspection_data(&mut g, &roots[0], verbose)?;
         report.image_path = Some(image.to_string_lossy().to_string());
+        if roots.len() > 1 {
+            report.os_instances = Some(
+                roots
+                    .iter()
+                    .map(|root| OsInstance {
+                        root: root.clone(),
+                        os_type: g.inspect_get_type(root).ok(),
+                        distribution: g.inspect_get_distro(root).ok(),
+                        product_name: g.inspect_get_product_name(root).ok(),
+                    })
+                    .collect(),
+            );
+        }
 
         g.shutdown()?;
 
+        super::catalog::record_inspection(image, &report);
+
         // Store in cache if caching is enabled
         if use_cache {
             if let Ok(cache) = InspectionCache::new() {
@@ -603,9 +733,17 @@ pub fn inspect_image(
 
     // Print Quick Summary first
     if !roots.is_empty() {
-        println!("\n╭─────────────────────────────────────────────────────────╮");
-        println!("│ {} {}", "✨ Quick Summary".truecolor(222, 115, 86).bold(), " ".repeat(38));
-        println!("╰─────────────────────────────────────────────────────────╯");
+        println!(
+            "\n{}",
+            plain::text("╭─────────────────────────────────────────────────────────╮")
+        );
+        println!(
+            "{} {} {}",
+            plain::text("│"),
+            plain::text("✨ Quick Summary").truecolor(222, 115, 86).bold(),
+            " ".repeat(38)
+        );
+        println!("{}", plain::text("╰─────────────────────────────────────────────────────────╯"));
 
         for root in &roots {
             if let Ok(ostype) = g.inspect_get_type(root) {
@@ -621,30 +759,48 @@ pub fn inspect_image(
                 let major = g.inspect_get_major_version(root).unwrap_or(0);
                 let minor = g.inspect_get_minor_version(root).unwrap_or(0);
 
-                print!("  {} {} ", os_icon, product.bright_green().bold());
+                print!("  {} {} ", plain::text(os_icon), product.bright_green().bold());
                 if major > 0 || minor > 0 {
                     print!("{} ", format!("v{}.{}", major, minor).bright_white());
                 }
                 println!("({})", distro.truecolor(222, 115, 86));
             }
         }
+        if roots.len() > 1 {
+            println!();
+            println!(
+                "  {} {} operating systems; other commands use {} unless --os-root picks another",
+                plain::text("ℹ️").bright_blue(),
+                roots.len(),
+                roots[0].bright_white().bold()
+            );
+        }
         println!();
     }
 
-    println!("{}", "🖥️  Operating Systems".truecolor(222, 115, 86).bold());
-    println!("{}", "─".repeat(60).bright_black());
+    println!("{}", plain::text("🖥️  Operating Systems").truecolor(222, 115, 86).bold());
+    println!("{}", plain::text("─").repeat(60).bright_black());
 
     if roots.is_empty() {
-        println!("  {} {}", "⚠️".yellow(), "No operating systems found".bright_black());
+        println!("  {} {}", plain::text("⚠️").yellow(), "No operating systems found".bright_black());
         if verbose {
             eprintln!("[VERBOSE] No bootable operating systems detected");
         }
     } else {
-        for root in &roots {
+        for (index, root) in roots.iter().enumerate() {
             if verbose {
                 eprintln!("[VERBOSE] Inspecting OS at root: {}", root);
             }
-            println!("  {} Root: {}", "🔹".truecolor(222, 115, 86), root.bright_white().bold());
+            if roots.len() > 1 {
+                println!(
+                    "  {} Root {}: {}",
+                    plain::text("🔹").truecolor(222, 115, 86),
+                    index + 1,
+                    root.bright_white().bold()
+                );
+            } else {
+                println!("  {} Root: {}", plain::text("🔹").truecolor(222, 115, 86), root.bright_white().bold());
+            }
             println!();
 
             if let Ok(ostype) = g.inspect_get_type(root) {
@@ -657,29 +813,29 @@ pub fn inspect_image(
                     "freebsd" => "👿",
                     _ => "💻",
                 };
-                println!("    {} Type:         {}", os_icon, ostype.bright_white().bold());
+                println!("    {} Type:         {}", plain::text(os_icon), ostype.bright_white().bold());
             }
             if let Ok(distro) = g.inspect_get_distro(root) {
                 if verbose {
                     eprintln!("[VERBOSE] Distribution: {}", distro);
                 }
                 if distro == "unknown" {
-                    println!("    {} Distribution: {}", "📦".bright_black(), distro.bright_black());
+                    println!("    {} Distribution: {}", plain::text("📦").bright_black(), distro.bright_black());
                 } else {
-                    println!("    {} Distribution: {}", "📦".green(), distro.bright_green().bold());
+                    println!("    {} Distribution: {}", plain::text("📦").green(), distro.bright_green().bold());
                 }
             }
             if let Ok(product) = g.inspect_get_product_name(root) {
                 if verbose {
                     eprintln!("[VERBOSE] Product name: {}", product);
                 }
-                println!("    {} Product:      {}", "🏷️".green(), product.bright_green().bold());
+                println!("    {} Product:      {}", plain::text("🏷️").green(), product.bright_green().bold());
             }
             if let Ok(arch) = g.inspect_get_arch(root) {
                 if verbose {
                     eprintln!("[VERBOSE] Architecture: {}", arch);
                 }
-                println!("    {} Architecture: {}", "⚙️".truecolor(222, 115, 86), arch.truecolor(222, 115, 86).bold());
+                println!("    {} Architecture: {}", plain::text("⚙️").truecolor(222, 115, 86), arch.truecolor(222, 115, 86).bold());
             }
             if let Ok(major) = g.inspect_get_major_version(root) {
                 if let Ok(minor) = g.inspect_get_minor_version(root) {
@@ -688,9 +844,9 @@ pub fn inspect_image(
                     }
                     let version = format!("{}.{}", major, minor);
                     if version == "0.0" {
-                        println!("    {} Version:      {}", "🔢".bright_black(), version.bright_black());
+                        println!("    {} Version:      {}", plain::text("🔢").bright_black(), version.bright_black());
                     } else {
-                        println!("    {} Version:      {}", "🔢".green(), version.bright_green().bold());
+                        println!("    {} Version:      {}", plain::text("🔢").green(), version.bright_green().bold());
                     }
                 }
             }
@@ -699,9 +855,9 @@ pub fn inspect_image(
                     eprintln!("[VERBOSE] Hostname: {}", hostname);
                 }
                 if hostname == "localhost" {
-                    println!("    {} Hostname:     {}", "🏠".bright_black(), hostname.bright_black());
+                    println!("    {} Hostname:     {}", plain::text("🏠").bright_black(), hostname.bright_black());
                 } else {
-                    println!("    {} Hostname:     {}", "🏠".blue(), hostname.bright_blue().bold());
+                    println!("    {} Hostname:     {}", plain::text("🏠").blue(), hostname.bright_blue().bold());
                 }
             }
             if let Ok(pkg_fmt) = g.inspect_get_package_format(root) {
@@ -715,9 +871,9 @@ pub fn inspect_image(
                     _ => "📦",
                 };
                 if pkg_fmt == "unknown" {
-                    println!("    {} Packages:     {}", pkg_icon, pkg_fmt.bright_black());
+                    println!("    {} Packages:     {}", plain::text(pkg_icon), pkg_fmt.bright_black());
                 } else {
-                    println!("    {} Packages:     {}", pkg_icon, pkg_fmt.bright_magenta().bold());
+                    println!("    {} Packages:     {}", plain::text(pkg_icon), pkg_fmt.bright_magenta().bold());
                 }
             }
 
@@ -727,9 +883,9 @@ pub fn inspect_image(
             }
             if let Ok(init) = g.inspect_get_init_system(root) {
                 if init == "unknown" {
-                    println!("    {} Init system:  {}", "⚡".bright_black(), init.bright_black());
+                    println!("    {} Init system:  {}", plain::text("⚡").bright_black(), init.bright_black());
                 } else {
-                    println!("    {} Init system:  {}", "⚡".yellow(), init.truecolor(222, 115, 86).bold());
+                    println!("    {} Init system:  {}", plain::text("⚡").yellow(), init.truecolor(222, 115, 86).bold());
                 }
             }
 
@@ -738,9 +894,9 @@ pub fn inspect_image(
             }
             if let Ok(pkg_mgr) = g.inspect_get_package_management(root) {
                 if pkg_mgr == "unknown" {
-                    println!("    {} Pkg Manager:  {}", "🔧".yellow(), pkg_mgr.bright_black());
+                    println!("    {} Pkg Manager:  {}", plain::text("🔧").yellow(), pkg_mgr.bright_black());
                 } else {
-                    println!("    {} Pkg Manager:  {}", "🔧".yellow(), pkg_mgr.bright_white().bold());
+                    println!("    {} Pkg Manager:  {}", plain::text("🔧").yellow(), pkg_mgr.bright_white().bold());
                 }
             }
 
@@ -748,7 +904,7 @@ pub fn inspect_image(
                 eprintln!("[VERBOSE] Checking OS format...");
             }
             if let Ok(format) = g.inspect_get_format(root) {
-                println!("    {} Format:       {}", "💿".yellow(), format.bright_white());
+                println!("    {} Format:       {}", plain::text("💿").yellow(), format.bright_white());
             }
 
             if verbose {
@@ -885,39 +1041,39 @@ pub fn inspect_image(
                 eprintln!("[VERBOSE] Gathering system configuration...");
             }
             println!();
-            println!("    {}", "⚙️  System Configuration".truecolor(222, 115, 86).bold());
-            println!("    {}", "─".repeat(56).bright_black());
+            println!("    {}", plain::text("⚙️  System Configuration").truecolor(222, 115, 86).bold());
+            println!("    {}", plain::text("─").repeat(56).bright_black());
 
             if let Ok(timezone) = g.inspect_timezone(root) {
                 if timezone == "unknown" {
-                    println!("      {} Timezone:    {}", "🌍".yellow(), timezone.bright_black());
+                    println!("      {} Timezone:    {}", plain::text("🌍").yellow(), timezone.bright_black());
                 } else {
-                    println!("      {} Timezone:    {}", "🌍".yellow(), timezone.bright_white().bold());
+                    println!("      {} Timezone:    {}", plain::text("🌍").yellow(), timezone.bright_white().bold());
                 }
             }
 
             if let Ok(locale) = g.inspect_locale(root) {
                 if locale == "unknown" {
-                    println!("      {} Locale:      {}", "🗣️".yellow(), locale.bright_black());
+                    println!("      {} Locale:      {}", plain::text("🗣️").yellow(), locale.bright_black());
                 } else {
-                    println!("      {} Locale:      {}", "🗣️".yellow(), locale.bright_white());
+                    println!("      {} Locale:      {}", plain::text("🗣️").yellow(), locale.bright_white());
                 }
             }
 
             // SELinux
             if let Ok(selinux) = g.inspect_selinux(root) {
                 match selinux.as_str() {
-                    "enforcing" => println!("      {} SELinux:     {}", "🔒", selinux.green().bold()),
-                    "permissive" => println!("      {} SELinux:     {}", "⚠️", selinux.yellow()),
-                    "disabled" => println!("      {} SELinux:     {}", "🔓", selinux.bright_black()),
-                    _ => println!("      {} SELinux:     {}", "❓", selinux.bright_black()),
+                    "enforcing" => println!("      {} SELinux:     {}", plain::text("🔒"), selinux.green().bold()),
+                    "permissive" => println!("      {} SELinux:     {}", plain::text("⚠️"), selinux.yellow()),
+                    "disabled" => println!("      {} SELinux:     {}", plain::text("🔓"), selinux.bright_black()),
+                    _ => println!("      {} SELinux:     {}", plain::text("❓"), selinux.bright_black()),
                 }
             }
 
             // Cloud-init
             if let Ok(has_cloud_init) = g.inspect_cloud_init(root) {
                 if has_cloud_init {
-                    println!("      {} Cloud-init:  {}", "☁️".yellow(), "yes".green().bold());
+                    println!("      {} Cloud-init:  {}", plain::text("☁️").yellow(), "yes".green().bold());
                 }
             }
 
@@ -927,7 +1083,7 @@ pub fn inspect_image(
             }
             if let Ok(vm_tools) = g.inspect_vm_tools(root) {
                 if !vm_tools.is_empty() {
-                    println!("      {} VM Tools:    {}", "🔧".yellow(), vm_tools.join(", ").bright_white().bold());
+                    println!("      {} VM Tools:    {}", plain::text("🔧").yellow(), vm_tools.join(", ").bright_white().bold());
                 }
             }
 
@@ -938,20 +1094,20 @@ pub fn inspect_image(
             if let Ok(interfaces) = g.inspect_network(root) {
                 if !interfaces.is_empty() {
                     println!();
-                    println!("    {}", "🌐 Network Configuration".truecolor(222, 115, 86).bold());
-                    println!("    {}", "─".repeat(56).bright_black());
+                    println!("    {}", plain::text("🌐 Network Configuration").truecolor(222, 115, 86).bold());
+                    println!("    {}", plain::text("─").repeat(56).bright_black());
                     for iface in &interfaces {
-                        println!("      {} Interface: {}", "📡".yellow(), iface.name.bright_white().bold());
+                        println!("      {} Interface: {}", plain::text("📡").yellow(), iface.name.bright_white().bold());
                         if !iface.ip_address.is_empty() {
-                            println!("        {} IP:   {}", "•".bright_black(), iface.ip_address.join(", ").bright_white());
+                            println!("        {} IP:   {}", plain::text("•").bright_black(), iface.ip_address.join(", ").bright_white());
                         }
                         if !iface.mac_address.is_empty() {
-                            println!("        {} MAC:  {}", "•".bright_black(), iface.mac_address.bright_black());
+                            println!("        {} MAC:  {}", plain::text("•").bright_black(), iface.mac_address.bright_black());
                         }
                         if iface.dhcp {
-                            println!("        {} DHCP: {}", "•".bright_black(), "yes".green().bold());
+                            println!("        {} DHCP: {}", plain::text("•").bright_black(), "yes".green().bold());
                         } else {
-                            println!("        {} DHCP: {}", "•".bright_black(), "no".bright_black());
+                            println!("        {} DHCP: {}", plain::text("•").bright_black(), "no".bright_black());
                         }
                     }
                 }
@@ -959,7 +1115,7 @@ pub fn inspect_image(
 
             if let Ok(dns_servers) = g.inspect_dns(root) {
                 if !dns_servers.is_empty() {
-                    println!("      {} DNS:  {}", "🌐".yellow(), dns_servers.join(", ").bright_white().bold());
+                    println!("      {} DNS:  {}", plain::text("🌐").yellow(), dns_servers.join(", ").bright_white().bold());
                 }
             }
 
@@ -986,26 +1142,26 @@ pub fn inspect_image(
 
                 if !regular_users.is_empty() || !system_users.is_empty() {
                     println!();
-                    println!("    {}", "👥 User Accounts".truecolor(222, 115, 86).bold());
-                    println!("    {}", "─".repeat(56).bright_black());
+                    println!("    {}", plain::text("👥 User Accounts").truecolor(222, 115, 86).bold());
+                    println!("    {}", plain::text("─").repeat(56).bright_black());
 
                     if !regular_users.is_empty() {
-                        println!("      {} Regular users: {}", "👤".yellow(), regular_users.len().to_string().bright_white().bold());
+                        println!("      {} Regular users: {}", plain::text("👤").yellow(), regular_users.len().to_string().bright_white().bold());
                         for user in regular_users.iter().take(10) {
                             println!("        {} {} {} {} {}",
-                                "•".bright_black(),
+                                plain::text("•").bright_black(),
                                 user.username.bright_white().bold(),
                                 format!("(uid: {})", user.uid).bright_black(),
-                                "→".bright_black(),
+                                plain::text("→").bright_black(),
                                 user.home.bright_black()
                             );
                         }
                         if regular_users.len() > 10 {
-                            println!("        {} and {} more...", "•".bright_black(), (regular_users.len() - 10).to_string().bright_black());
+                            println!("        {} and {} more...", plain::text("•").bright_black(), (regular_users.len() - 10).to_string().bright_black());
                         }
                     }
 
-                    println!("      {} System users: {}", "⚙️".bright_black(), system_users.len().to_string().bright_black());
+                    println!("      {} System users: {}", plain::text("⚙️").bright_black(), system_users.len().to_string().bright_black());
                 }
             }
 
@@ -1016,23 +1172,23 @@ pub fn inspect_image(
             if let Ok(ssh_config) = g.inspect_ssh_config(root) {
                 if !ssh_config.is_empty() {
                     println!();
-                    println!("    {}", "🔐 SSH Configuration".truecolor(222, 115, 86).bold());
-                    println!("    {}", "─".repeat(56).bright_black());
+                    println!("    {}", plain::text("🔐 SSH Configuration").truecolor(222, 115, 86).bold());
+                    println!("    {}", plain::text("─").repeat(56).bright_black());
                     if let Some(port) = ssh_config.get("Port") {
-                        println!("      {} Port: {}", "•".bright_black(), port.bright_white().bold());
+                        println!("      {} Port: {}", plain::text("•").bright_black(), port.bright_white().bold());
                     }
                     if let Some(permit_root) = ssh_config.get("PermitRootLogin") {
                         if permit_root == "yes" {
-                            println!("      {} PermitRootLogin: {}", "•".bright_black(), permit_root.red());
+                            println!("      {} PermitRootLogin: {}", plain::text("•").bright_black(), permit_root.red());
                         } else {
-                            println!("      {} PermitRootLogin: {}", "•".bright_black(), permit_root.green());
+                            println!("      {} PermitRootLogin: {}", plain::text("•").bright_black(), permit_root.green());
                         }
                     }
                     if let Some(password_auth) = ssh_config.get("PasswordAuthentication") {
                         if password_auth == "no" {
-                            println!("      {} PasswordAuth: {}", "•".bright_black(), password_auth.green());
+                            println!("      {} PasswordAuth: {}", plain::text("•").bright_black(), password_auth.green());
                         } else {
-                            println!("      {} PasswordAuth: {}", "•".bright_black(), password_auth.yellow());
+                            println!("      {} PasswordAuth: {}", plain::text("•").bright_black(), password_auth.yellow());
                         }
                     }
                 }
@@ -1045,14 +1201,66 @@ pub fn inspect_image(
             if let Ok(services) = g.inspect_systemd_services(root) {
                 if !services.is_empty() {
                     println!();
-                    println!("    {}", "⚙️  Systemd Services".truecolor(222, 115, 86).bold());
-                    println!("    {}", "─".repeat(56).bright_black());
-                    println!("      {} Enabled: {}", "✓".green(), services.len().to_string().bright_white().bold());
+                    println!("    {}", plain::text("⚙️  Systemd Services").truecolor(222, 115, 86).bold());
+                    println!("    {}", plain::text("─").repeat(56).bright_black());
+                    println!("      {} Enabled: {}", plain::text("✓").green(), services.len().to_string().bright_white().bold());
                     for service in services.iter().take(15) {
-                        println!("        {} {}", "•".bright_black(), service.name.bright_white());
+                        println!("        {} {}", plain::text("•").bright_black(), service.name.bright_white());
                     }
                     if services.len() > 15 {
-                        println!("        {} and {} more...", "•".bright_black(), (services.len() - 15).to_string().bright_black());
+                        println!("        {} and {} more...", plain::text("•").bright_black(), (services.len() - 15).to_string().bright_black());
+                    }
+                }
+            }
+
+            // Windows Services and Scheduled Tasks
+            if g.inspect_get_type(root).map(|t| t == "windows").unwrap_or(false) {
+                if verbose {
+                    eprintln!("[VERBOSE] Listing Windows services and scheduled tasks...");
+                }
+                if let Ok(services) = g.inspect_windows_services(root) {
+                    let services: Vec<_> = services.iter()
+                        .filter(|s| !s.service_type.ends_with("Driver"))
+                        .collect();
+                    if !services.is_empty() {
+                        let automatic = services.iter().filter(|s| s.start_type == "Automatic").count();
+                        println!();
+                        println!("    {}", plain::text("⚙️  Windows Services").truecolor(222, 115, 86).bold());
+                        println!("    {}", plain::text("─").repeat(56).bright_black());
+                        println!("      {} Automatic: {} of {}", plain::text("✓").green(), automatic.to_string().bright_white().bold(), services.len());
+                        for service in services.iter().filter(|s| s.start_type == "Automatic").take(15) {
+                            println!("        {} {} {}",
+                                plain::text("•").bright_black(),
+                                service.name.bright_white(),
+                                format!("({})", service.account.as_deref().unwrap_or("LocalSystem")).bright_black()
+                            );
+                        }
+                        if automatic > 15 {
+                            println!("        {} and {} more...", plain::text("•").bright_black(), (automatic - 15).to_string().bright_black());
+                        }
+                    }
+                }
+                if let Ok(tasks) = g.inspect_windows_scheduled_tasks(root) {
+                    if !tasks.is_empty() {
+                        let enabled: Vec<_> = tasks.iter().filter(|t| t.enabled).collect();
+                        println!();
+                        println!("    {}", plain::text("⏰ Scheduled Tasks").truecolor(222, 115, 86).bold());
+                        println!("    {}", plain::text("─").repeat(56).bright_black());
+                        println!("      {} Enabled: {} of {}", plain::text("✓").green(), enabled.len().to_string().bright_white().bold(), tasks.len());
+                        // Tasks outside \Microsoft are the ones worth a look
+                        let third_party: Vec<_> = enabled.iter()
+                            .filter(|t| !t.path.starts_with("\\Microsoft\\"))
+                            .collect();
+                        for task in third_party.iter().take(15) {
+                            println!("        {} {} {}",
+                                plain::text("•").bright_black(),
+                                task.path.bright_white(),
+                                task.command.as_deref().unwrap_or("").bright_black()
+                            );
+                        }
+                        if third_party.len() > 15 {
+                            println!("        {} and {} more...", plain::text("•").bright_black(), (third_party.len() - 15).to_string().bright_black());
+                        }
                     }
                 }
             }
@@ -1064,8 +1272,8 @@ pub fn inspect_image(
             if let Ok(runtimes) = g.inspect_runtimes(root) {
                 if !runtimes.is_empty() {
                     println!();
-                    println!("    {}", "💻 Language Runtimes".truecolor(222, 115, 86).bold());
-                    println!("    {}", "─".repeat(56).bright_black());
+                    println!("    {}", plain::text("💻 Language Runtimes").truecolor(222, 115, 86).bold());
+                    println!("    {}", plain::text("─").repeat(56).bright_black());
 
                     // Define icons for each runtime
                     for (runtime, _version) in &runtimes {
@@ -1078,7 +1286,7 @@ pub fn inspect_image(
                             "perl" => ("🐪", "Perl"),
                             _ => ("📦", runtime.as_str()),
                         };
-                        println!("      {} {}", icon, name.bright_white().bold());
+                        println!("      {} {}", plain::text(icon), name.bright_white().bold());
                     }
                 }
             }
@@ -1090,8 +1298,8 @@ pub fn inspect_image(
             if let Ok(container_runtimes) = g.inspect_container_runtimes(root) {
                 if !container_runtimes.is_empty() {
                     println!();
-                    println!("    {}", "🐳 Container Runtimes".truecolor(222, 115, 86).bold());
-                    println!("    {}", "─".repeat(56).bright_black());
+                    println!("    {}", plain::text("🐳 Container Runtimes").truecolor(222, 115, 86).bold());
+                    println!("    {}", plain::text("─").repeat(56).bright_black());
                     for runtime in &container_runtimes {
                         let (icon, name) = match runtime.as_str() {
                             "docker" => ("🐳", "Docker"),
@@ -1100,7 +1308,7 @@ pub fn inspect_image(
                             "cri-o" => ("🔷", "CRI-O"),
                             _ => ("📦", runtime.as_str()),
                         };
-                        println!("      {} {}", icon, name.bright_white().bold());
+                        println!("      {} {}", plain::text(icon), name.bright_white().bold());
                     }
                 }
             }
@@ -1115,18 +1323,18 @@ pub fn inspect_image(
                     || !lvm_info.logical_volumes.is_empty()
                 {
                     println!();
-                    println!("    {}", "💾 LVM Configuration".truecolor(222, 115, 86).bold());
-                    println!("    {}", "─".repeat(56).bright_black());
+                    println!("    {}", plain::text("💾 LVM Configuration").truecolor(222, 115, 86).bold());
+                    println!("    {}", plain::text("─").repeat(56).bright_black());
                     if !lvm_info.physical_volumes.is_empty() {
-                        println!("      {} Physical Volumes: {}", "🔷".bright_blue(), lvm_info.physical_volumes.join(", ").bright_white());
+                        println!("      {} Physical Volumes: {}", plain::text("🔷").bright_blue(), lvm_info.physical_volumes.join(", ").bright_white());
                     }
                     if !lvm_info.volume_groups.is_empty() {
                         let vg_names = lvm_info.volume_groups.iter().map(|vg| vg.name.as_str()).collect::<Vec<_>>().join(", ");
-                        println!("      {} Volume Groups: {}", "📦".yellow(), vg_names.bright_white().bold());
+                        println!("      {} Volume Groups: {}", plain::text("📦").yellow(), vg_names.bright_white().bold());
                     }
                     if !lvm_info.logical_volumes.is_empty() {
                         let lv_names = lvm_info.logical_volumes.iter().map(|lv| lv.name.as_str()).collect::<Vec<_>>().join(", ");
-                        println!("      {} Logical Volumes: {}", "💿".truecolor(222, 115, 86), lv_names.bright_white());
+                        println!("      {} Logical Volumes: {}", plain::text("💿").truecolor(222, 115, 86), lv_names.bright_white());
                     }
                 }
             }
@@ -1252,6 +1460,8 @@ pub fn execute_command(image: &PathBuf, command: &[String], verbose: bool) -> Re
     let progress = ProgressReporter::spinner("Loading disk image...");
 
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
@@ -1265,10 +1475,7 @@ pub fn execute_command(image: &PathBuf, command: &[String], verbose: bool) -> Re
     }
 
     progress.set_message("Mounting filesystems...");
-    let mountpoints = g.inspect_get_mountpoints(&roots[0])?;
-    for (mp, device) in mountpoints {
-        g.mount(&device, &mp)?;
-    }
+    g.mount_os_ro(&roots[0])?;
 
     // Execute command
     progress.set_message(format!("Executing command: {}", command.join(" ")));
@@ -1284,13 +1491,22 @@ pub fn execute_command(image: &PathBuf, command: &[String], verbose: bool) -> Re
     Ok(())
 }
 
-/// Backup files from guest to host
+/// Backup files from guest to a host archive
 pub fn backup_files(
     image: &PathBuf,
     guest_path: &str,
-    output_tar: &PathBuf,
+    output: &PathBuf,
+    format: Option<&str>,
+    options: &guestkit::guestfs::ArchiveOptions,
     verbose: bool,
 ) -> Result<()> {
+    use guestkit::guestfs::ArchiveFormat;
+
+    let format = match format {
+        Some(format) => format.parse::<ArchiveFormat>()?,
+        None => ArchiveFormat::from_path(output).unwrap_or(ArchiveFormat::TarGz),
+    };
+
     let mut g = Guestfs::new()?;
     g.set_verbose(verbose);
 
@@ -1301,6 +1517,8 @@ pub fn backup_files(
     ));
 
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
@@ -1314,36 +1532,21 @@ pub fn backup_files(
     }
 
     progress.set_message("Mounting filesystems...");
-    let mountpoints = g.inspect_get_mountpoints(&roots[0])?;
-    for (mp, device) in mountpoints {
-        g.mount(&device, &mp)?;
-    }
-
-    // Create tar archive in guest
-    progress.set_message(format!("Creating archive from {}...", guest_path));
-    let temp_tar = "/tmp/backup.tar.gz";
-    g.tar_out_opts(
-        guest_path,
-        temp_tar,
-        Some("gzip"),
-        false,
-        false,
-        false,
-        false,
-    )?;
-
-    // Download to host
-    progress.set_message("Downloading archive...");
-    g.download(temp_tar, output_tar.to_str().unwrap())?;
+    g.mount_os_ro(&roots[0])?;
 
-    let size = g.filesize(temp_tar)?;
+    progress.set_message(format!("Creating {} archive from {}...", format, guest_path));
+    let summary = g.archive_out(guest_path, output, format, options)?;
+    let size = std::fs::metadata(output)?.len();
 
     progress.finish_and_clear();
 
     println!(
-        "✓ Backup complete: {} bytes to {}",
+        "{} Backup complete: {} entries ({} bytes of files), {} bytes to {}",
+        plain::text("✓"),
+        summary.entries,
+        summary.bytes,
         size,
-        output_tar.display()
+        output.display()
     );
 
     g.umount_all()?;
@@ -1366,7 +1569,7 @@ pub fn create_disk(path: &PathBuf, size_mb: u64, format: &str, verbose: bool) ->
     let size_bytes = (size_mb * 1024 * 1024) as i64;
     g.disk_create(path.to_str().unwrap(), format, size_bytes)?;
 
-    println!("✓ Disk created successfully");
+    println!("{} Disk created successfully", plain::text("✓"));
 
     Ok(())
 }
@@ -1380,6 +1583,8 @@ pub fn check_filesystem(image: &PathBuf, device: Option<String>, verbose: bool)
         ProgressReporter::spinner(&format!("Checking filesystem on {}", image.display()));
 
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
@@ -1404,10 +1609,7 @@ pub fn check_filesystem(image: &PathBuf, device: Option<String>, verbose: bool)
 
     progress.finish_and_clear();
 
-    println!(
-        "✓ Filesystem check complete for {} ({})",
-        check_device, fstype
-    );
+    println!("{} Filesystem check complete for {} ({})", plain::text("✓"), check_device, fstype);
 
     g.shutdown()?;
     Ok(())
@@ -1421,6 +1623,8 @@ pub fn show_disk_usage(image: &PathBuf, verbose: bool) -> Result<()> {
     let progress = ProgressReporter::spinner("Loading disk image...");
 
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
@@ -1434,10 +1638,7 @@ pub fn show_disk_usage(image: &PathBuf, verbose: bool) -> Result<()> {
     }
 
     progress.set_message("Mounting filesystems...");
-    let mountpoints = g.inspect_get_mountpoints(&roots[0])?;
-    for (mp, device) in mountpoints {
-        g.mount(&device, &mp)?;
-    }
+    g.mount_os_ro(&roots[0])?;
 
     // Get disk usage
     progress.set_message("Calculating disk usage...");
@@ -1466,6 +1667,8 @@ pub fn diff_images(
     let mut g1 = Guestfs::new()?;
     g1.set_verbose(verbose);
     g1.add_drive_ro(image1.to_str().unwrap())?;
+    target::add_disks(&mut g1, true)?;
+    target::select(&mut g1);
     g1.launch()?;
 
     let roots1 = g1.inspect_os()?;
@@ -1482,6 +1685,8 @@ pub fn diff_images(
     let mut g2 = Guestfs::new()?;
     g2.set_verbose(verbose);
     g2.add_drive_ro(image2.to_str().unwrap())?;
+    target::add_disks(&mut g2, true)?;
+    target::select(&mut g2);
     g2.launch()?;
 
     let roots2 = g2.inspect_os()?;
@@ -1522,6 +1727,8 @@ pub fn compare_images(baseline: &PathBuf, images: &[PathBuf], verbose: bool) ->
     let mut g_baseline = Guestfs::new()?;
     g_baseline.set_verbose(verbose);
     g_baseline.add_drive_ro(baseline.to_str().unwrap())?;
+    target::add_disks(&mut g_baseline, true)?;
+    target::select(&mut g_baseline);
     g_baseline.launch()?;
 
     let roots_baseline = g_baseline.inspect_os()?;
@@ -1547,6 +1754,8 @@ pub fn compare_images(baseline: &PathBuf, images: &[PathBuf], verbose: bool) ->
         let mut g = Guestfs::new()?;
         g.set_verbose(verbose);
         g.add_drive_ro(image.to_str().unwrap())?;
+        target::add_disks(&mut g, true)?;
+        target::select(&mut g);
         g.launch()?;
 
         let roots = g.inspect_os()?;
@@ -1610,8 +1819,11 @@ pub fn compare_images(baseline: &PathBuf, images: &[PathBuf], verbose: bool) ->
 }
 
 /// Inspect multiple disk images in batch mode
+///
+/// Each result is written to `journal` as soon as it is known; images the
+/// journal already has a result for are not inspected again.
 pub fn inspect_batch(
-    images: &[PathBuf],
+    journal: &super::journal::Journal,
     parallel: usize,
     verbose: bool,
     output_format: Option<OutputFormat>,
@@ -1621,83 +1833,115 @@ pub fn inspect_batch(
     use std::sync::{Arc, Mutex};
     use std::thread;
 
+    let images: Vec<PathBuf> = journal
+        .items()
+        .iter()
+        .filter(|item| !journal.is_completed(item))
+        .map(PathBuf::from)
+        .collect();
+
     println!("=== Batch Inspection ===");
-    println!("Images: {}", images.len());
+    println!("Images: {}", journal.items().len());
+    if !journal.completed().is_empty() {
+        println!("Already inspected: {}", journal.completed().len());
+    }
     println!("Parallel workers: {}", parallel);
+    println!("Journal: {}", journal.path().display());
     println!();
 
-    // Shared results vector
+    // Shared results vector, starting with those of interrupted runs
+    let earlier: Vec<(String, Result<InspectionReport>)> = journal
+        .completed()
+        .iter()
+        .map(|record| {
+            let report = record
+                .result
+                .clone()
+                .context("No result in journal")
+                .and_then(|result| {
+                    serde_json::from_value(result).context("Unreadable result in journal")
+                });
+            (record.item.clone(), report)
+        })
+        .collect();
     let results: Arc<Mutex<Vec<(String, Result<InspectionReport>)>>> =
-        Arc::new(Mutex::new(Vec::new()));
+        Arc::new(Mutex::new(earlier));
 
     // Create work queue
-    let work_queue: Arc<Mutex<Vec<PathBuf>>> = Arc::new(Mutex::new(images.to_vec()));
+    let total = images.len();
+    let work_queue: Arc<Mutex<Vec<PathBuf>>> = Arc::new(Mutex::new(images));
 
     // Progress tracking
-    let total = images.len();
     let completed = Arc::new(Mutex::new(0usize));
 
-    // Spawn worker threads
-    let mut handles = vec![];
-
-    for worker_id in 0..parallel {
-        let work_queue = Arc::clone(&work_queue);
-        let results = Arc::clone(&results);
-        let completed = Arc::clone(&completed);
+    // Spawn worker threads, all joined at the end of the scope
+    thread::scope(|scope| {
+        for worker_id in 0..parallel {
+            let work_queue = Arc::clone(&work_queue);
+            let results = Arc::clone(&results);
+            let completed = Arc::clone(&completed);
+
+            scope.spawn(move || {
+                loop {
+                    // Get next image from queue
+                    let image = {
+                        let mut queue = work_queue.lock().unwrap();
+                        if queue.is_empty() {
+                            break;
+                        }
+                        queue.pop().unwrap()
+                    };
 
-        let handle = thread::spawn(move || {
-            loop {
-                // Get next image from queue
-                let image = {
-                    let mut queue = work_queue.lock().unwrap();
-                    if queue.is_empty() {
-                        break;
+                    if verbose {
+                        eprintln!("[Worker {}] Processing: {}", worker_id, image.display());
                     }
-                    queue.pop().unwrap()
-                };
-
-                if verbose {
-                    eprintln!("[Worker {}] Processing: {}", worker_id, image.display());
-                }
 
-                // Try cache first if enabled
-                let report_result = if use_cache {
-                    if let Ok(cache) = InspectionCache::new() {
-                        if let Ok(Some(cached)) = cache.get(&image) {
-                            eprintln!("✓ [Worker {}] Cache hit: {}", worker_id, image.display());
-                            Ok(cached)
+                    // Try cache first if enabled
+                    let report_result = if use_cache {
+                        if let Ok(cache) = InspectionCache::new() {
+                            if let Ok(Some(cached)) = cache.get(&image) {
+                                eprintln!(
+                                    "{} [Worker {}] Cache hit: {}",
+                                    plain::text("✓"),
+                                    worker_id,
+                                    image.display()
+                                );
+                                Ok(cached)
+                            } else {
+                                inspect_single_image(&image, verbose, use_cache)
+                            }
                         } else {
                             inspect_single_image(&image, verbose, use_cache)
                         }
                     } else {
                         inspect_single_image(&image, verbose, use_cache)
+                    };
+
+                    // Journal the result before anything else can go wrong
+                    let outcome = match &report_result {
+                        Ok(report) => serde_json::to_value(report).map_err(|e| e.to_string()),
+                        Err(e) => Err(format!("{:#}", e)),
+                    };
+                    if let Err(e) = journal.record(&image.to_string_lossy(), outcome) {
+                        eprintln!("{}  {:#}", plain::text("⚠️"), e);
                     }
-                } else {
-                    inspect_single_image(&image, verbose, use_cache)
-                };
 
-                // Store result
-                {
-                    let mut res = results.lock().unwrap();
-                    res.push((image.to_string_lossy().to_string(), report_result));
-                }
+                    // Store result
+                    {
+                        let mut res = results.lock().unwrap();
+                        res.push((image.to_string_lossy().to_string(), report_result));
+                    }
 
-                // Update progress
-                {
-                    let mut count = completed.lock().unwrap();
-                    *count += 1;
-                    eprintln!("Progress: {}/{}", *count, total);
+                    // Update progress
+                    {
+                        let mut count = completed.lock().unwrap();
+                        *count += 1;
+                        eprintln!("Progress: {}/{}", *count, total);
+                    }
                 }
-            }
-        });
-
-        handles.push(handle);
-    }
-
-    // Wait for all workers to complete
-    for handle in handles {
-        handle.join().unwrap();
-    }
+            });
+        }
+    });
 
     println!("\n=== Results ===\n");
 
@@ -1720,7 +1964,7 @@ pub fn inspect_batch(
                     println!();
                 } else {
                     // Summary output
-                    println!("✓ {}", image_path);
+                    println!("{} {}", plain::text("✓"), image_path);
                     println!(
                         "  OS: {} {}",
                         report.os.distribution.as_deref().unwrap_or("Unknown"),
@@ -1742,7 +1986,7 @@ pub fn inspect_batch(
             }
             Err(e) => {
                 error_count += 1;
-                println!("✗ {}", image_path);
+                println!("{} {}", plain::text("✗"), image_path);
                 println!("  Error: {}", e);
                 println!();
             }
@@ -1753,6 +1997,7 @@ pub fn inspect_batch(
     println!("Total: {}", final_results.len());
     println!("Success: {}", success_count);
     println!("Errors: {}", error_count);
+    println!("Journal: {}", journal.path().display());
 
     Ok(())
 }
@@ -1769,6 +2014,8 @@ fn inspect_single_image(
     g.set_verbose(false); // Disable verbose for batch mode to reduce noise
 
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
     g.launch()?;
 
     let roots = g.inspect_os()?;
@@ -1782,6 +2029,8 @@ fn inspect_single_image(
 
     g.shutdown()?;
 
+    super::catalog::record_inspection(image, &report);
+
     // Store in cache if enabled
     if use_cache {
         if let Ok(cache) = InspectionCache::new() {
@@ -1793,7 +2042,12 @@ fn inspect_single_image(
 }
 
 /// List filesystems and partitions
-pub fn list_filesystems(image: &PathBuf, detailed: bool, verbose: bool) -> Result<()> {
+pub fn list_filesystems(
+    image: &PathBuf,
+    detailed: bool,
+    subvolumes: bool,
+    verbose: bool,
+) -> Result<()> {
     use guestkit::core::ProgressReporter;
     use guestkit::Guestfs;
     use owo_colors::OwoColorize;
@@ -1808,6 +2062,8 @@ pub fn list_filesystems(image: &PathBuf, detailed: bool, verbose: bool) -> Resul
 
     g.add_drive_ro(image.to_str().unwrap())
         .with_context(|| format!("Failed to add disk: {}", image.display()))?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch().context("Failed to launch appliance")?;
@@ -1818,19 +2074,19 @@ pub fn list_filesystems(image: &PathBuf, detailed: bool, verbose: bool) -> Resul
 
     progress.finish_and_clear();
 
-    println!("\n{}", "═".repeat(70).bright_blue());
+    println!("\n{}", plain::text("═").repeat(70).bright_blue());
     println!(
         "{} {}",
-        "💾 Disk Image:".truecolor(222, 115, 86).bold(),
+        plain::text("💾 Disk Image:").truecolor(222, 115, 86).bold(),
         image.display().to_string().bright_white()
     );
-    println!("{}\n", "═".repeat(70).bright_blue());
+    println!("{}\n", plain::text("═").repeat(70).bright_blue());
 
     // Devices
     println!("{}", "Block Devices".bright_white().bold());
-    println!("{}", "─".repeat(50).bright_black());
+    println!("{}", plain::text("─").repeat(50).bright_black());
     for device in devices {
-        println!("  {} {}", "▪".truecolor(222, 115, 86), device.bright_white().bold());
+        println!("  {} {}", plain::text("▪").truecolor(222, 115, 86), device.bright_white().bold());
 
         if detailed {
             if let Ok(size) = g.blockdev_getsize64(&device) {
@@ -1857,7 +2113,7 @@ pub fn list_filesystems(image: &PathBuf, detailed: bool, verbose: bool) -> Resul
     let partitions = g.list_partitions().context("Failed to list partitions")?;
     if !partitions.is_empty() {
         println!("\n{}", "Partitions".bright_white().bold());
-        println!("{}", "─".repeat(50).bright_black());
+        println!("{}", plain::text("─").repeat(50).bright_black());
 
         for partition in partitions {
             let fstype = g
@@ -1878,7 +2134,7 @@ pub fn list_filesystems(image: &PathBuf, detailed: bool, verbose: bool) -> Resul
 
             println!(
                 "  {} {} {} {}",
-                fs_icon,
+                plain::text(fs_icon),
                 partition.bright_white().bold(),
                 format!("({})", fstype).truecolor(222, 115, 86),
                 format!("{:.1} GiB", gb).truecolor(222, 115, 86)
@@ -1912,37 +2168,159 @@ pub fn list_filesystems(image: &PathBuf, detailed: bool, verbose: bool) -> Resul
     if let Ok(vgs) = g.vgs() {
         if !vgs.is_empty() {
             println!("\n{}", "LVM Volume Groups".bright_white().bold());
-            println!("{}", "─".repeat(50).bright_black());
+            println!("{}", plain::text("─").repeat(50).bright_black());
             for vg in vgs {
-                println!("  {} {}", "▸".bright_magenta(), vg.bright_white().bold());
+                println!("  {} {}", plain::text("▸").bright_magenta(), vg.bright_white().bold());
             }
         }
     }
 
     if let Ok(lvs) = g.lvs() {
         if !lvs.is_empty() {
+            let layouts = g.logical_volumes().unwrap_or_default();
             println!("\n{}", "LVM Logical Volumes".bright_white().bold());
-            println!("{}", "─".repeat(50).bright_black());
+            println!("{}", plain::text("─").repeat(50).bright_black());
             for lv in lvs {
                 let size = g.blockdev_getsize64(&lv).unwrap_or(0);
                 let gb = size as f64 / 1_073_741_824.0;
 
+                // Thin, cache and RAID LVs: say which and from which pool
+                let layout = layouts
+                    .iter()
+                    .find(|layout| layout.path == lv && layout.lv_type != LvType::Linear)
+                    .map(|layout| match &layout.pool {
+                        Some(pool) => format!(" {} ({})", layout.lv_type, pool),
+                        None => format!(" {}", layout.lv_type),
+                    })
+                    .unwrap_or_default();
+
                 println!(
-                    "  {} {} {}",
-                    "▸".bright_magenta(),
+                    "  {} {} {}{}",
+                    plain::text("▸").bright_magenta(),
                     lv.bright_white().bold(),
-                    format!("{:.1} GiB", gb).truecolor(222, 115, 86)
+                    format!("{:.1} GiB", gb).truecolor(222, 115, 86),
+                    layout.bright_black()
                 );
             }
         }
     }
 
-    println!("\n{}", "═".repeat(70).bright_blue());
+    if subvolumes {
+        let mut btrfs: Vec<String> = g
+            .list_filesystems()
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|(_, fstype)| fstype == "btrfs")
+            .map(|(device, _)| device)
+            .collect();
+        for lv in g.lvs().unwrap_or_default() {
+            let lv = lv.trim().to_string();
+            if g.vfs_type(&lv).is_ok_and(|fstype| fstype == "btrfs") {
+                btrfs.push(lv);
+            }
+        }
+        btrfs.sort();
+
+        if btrfs.is_empty() {
+            println!("\n{}", "No btrfs filesystems".dimmed());
+        }
+        for device in btrfs {
+            print_btrfs_subvolumes(&mut g, &device);
+        }
+    }
+
+    println!("\n{}", plain::text("═").repeat(70).bright_blue());
 
     g.shutdown().ok();
     Ok(())
 }
 
+/// Print the subvolumes and snapshots of a btrfs filesystem with their sizes
+fn print_btrfs_subvolumes(g: &mut guestkit::Guestfs, device: &str) {
+    use guestkit::guestfs::btrfs::{btrfsvol_mountable, BTRFS_TOP_LEVEL_ID};
+    use owo_colors::OwoColorize;
+
+    println!(
+        "\n{} {}",
+        "Btrfs Subvolumes".bright_white().bold(),
+        format!("({})", device).truecolor(222, 115, 86)
+    );
+    println!("{}", plain::text("─").repeat(50).bright_black());
+
+    // The top level shows every subvolume whichever is the default
+    let top_level = btrfsvol_mountable(device, "");
+    if let Err(e) = g.mount_ro(&top_level, "/") {
+        println!("  {} {}", "Cannot mount:".dimmed(), e);
+        return;
+    }
+
+    let subvolumes = match g.btrfs_subvolumes("/") {
+        Ok(subvolumes) => subvolumes,
+        Err(e) => {
+            println!("  {} {}", "Cannot list subvolumes:".dimmed(), e);
+            let _ = g.umount(&top_level);
+            return;
+        }
+    };
+    let default_id = g
+        .btrfs_subvolume_get_default("/")
+        .ok()
+        .and_then(|id| u64::try_from(id).ok());
+
+    if subvolumes.is_empty() {
+        println!("  {}", "No subvolumes".dimmed());
+    } else {
+        println!(
+            "  {}",
+            format!("{:<6} {:>9} {:>10}  {}", "ID", "Size", "Exclusive", "Path").dimmed()
+        );
+    }
+
+    for subvolume in &subvolumes {
+        let (size, exclusive) = match g.btrfs_subvolume_usage(&format!("/{}", subvolume.path)) {
+            Ok(usage) => (format_size(usage.total), format_size(usage.exclusive)),
+            Err(_) => ("-".to_string(), "-".to_string()),
+        };
+
+        let mut notes = Vec::new();
+        if default_id == Some(subvolume.id) {
+            notes.push("default".to_string());
+        }
+        if let Some(parent_uuid) = &subvolume.parent_uuid {
+            let origin = subvolumes
+                .iter()
+                .find(|other| other.uuid.as_ref() == Some(parent_uuid))
+                .map(|other| other.path.as_str())
+                .unwrap_or("deleted subvolume");
+            notes.push(format!("snapshot of {}", origin));
+        }
+
+        let path = if subvolume.is_snapshot() {
+            subvolume.path.bright_cyan().to_string()
+        } else {
+            subvolume.path.bright_white().bold().to_string()
+        };
+        println!(
+            "  {:<6} {} {}  {} {}",
+            subvolume.id,
+            format!("{:>9}", size).truecolor(222, 115, 86),
+            format!("{:>10}", exclusive).truecolor(222, 115, 86),
+            path,
+            if notes.is_empty() {
+                String::new()
+            } else {
+                format!("({})", notes.join(", ")).dimmed().to_string()
+            }
+        );
+    }
+
+    if default_id == Some(BTRFS_TOP_LEVEL_ID) && !subvolumes.is_empty() {
+        println!("  {} top level", "Default:".dimmed());
+    }
+
+    let _ = g.umount(&top_level);
+}
+
 /// List installed packages
 pub fn list_packages(
     image: &PathBuf,
@@ -1971,6 +2349,8 @@ pub fn list_packages(
 
     g.add_drive_ro(image.to_str().unwrap())
         .with_context(|| format!("Failed to add disk: {}", image.display()))?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     if let Some(ref p) = progress {
         p.set_message("Launching appliance...");
@@ -1997,13 +2377,7 @@ pub fn list_packages(
         p.set_message("Mounting filesystems...");
     }
 
-    if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-        let mut mounts: Vec<_> = mountpoints.iter().collect();
-        mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-        for (mount, device) in mounts {
-            g.mount_ro(device, mount).ok();
-        }
-    }
+    g.mount_os_ro(root).ok();
 
     // List packages
     if let Some(ref p) = progress {
@@ -2065,25 +2439,16 @@ pub fn list_packages(
             println!("{}", "-".repeat(82));
 
             for app in limited {
-                let name = if app.name.len() > 38 {
-                    format!("{}...", &app.name[..35])
-                } else {
-                    app.name.clone()
-                };
-
-                let version = if app.version.len() > 18 {
-                    format!("{}...", &app.version[..15])
-                } else {
-                    app.version.clone()
-                };
-
-                let release = if app.release.len() > 18 {
-                    format!("{}...", &app.release[..15])
-                } else {
-                    app.release.clone()
-                };
+                let name = truncate_display(&app.name, 38);
+                let version = truncate_display(&app.version, 18);
+                let release = truncate_display(&app.release, 18);
 
-                println!("{:<40} {:<20} {:<20}", name, version, release);
+                println!(
+                    "{} {} {}",
+                    pad_display(&name, 40),
+                    pad_display(&version, 20),
+                    pad_display(&release, 20)
+                );
             }
         }
     }
@@ -2110,26 +2475,22 @@ fn mount_disk_for_systemd(image: &Path, verbose: bool) -> Result<(Guestfs, Strin
 
     g.add_drive_ro(image.to_str().unwrap())
         .with_context(|| format!("Failed to add disk: {}", image.display()))?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch().context("Failed to launch appliance")?;
 
     progress.set_message("Mounting filesystems...");
 
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if roots.is_empty() {
         progress.abandon_with_message("No operating systems found");
         anyhow::bail!("No operating systems found in image");
     }
 
     let root = roots[0].clone();
-    if let Ok(mountpoints) = g.inspect_get_mountpoints(&root) {
-        let mut mounts: Vec<_> = mountpoints.iter().collect();
-        mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-        for (mount, device) in mounts {
-            g.mount_ro(device, mount).ok();
-        }
-    }
+    g.mount_os_ro(&root).ok();
 
     progress.finish_and_clear();
 
@@ -2137,6 +2498,7 @@ fn mount_disk_for_systemd(image: &Path, verbose: bool) -> Result<(Guestfs, Strin
 }
 
 /// Analyze systemd journal logs
+#[allow(clippy::too_many_arguments)]
 pub fn systemd_journal_command(
     image: &PathBuf,
     priority: Option<u8>,
@@ -2145,6 +2507,10 @@ pub fn systemd_journal_command(
     warnings: bool,
     stats: bool,
     limit: Option<usize>,
+    boot: Option<&str>,
+    list_boots: bool,
+    verify: bool,
+    verify_key: Option<&str>,
     verbose: bool,
 ) -> Result<()> {
     let (mut g, _root) = mount_disk_for_systemd(image, verbose)?;
@@ -2153,31 +2519,120 @@ pub fn systemd_journal_command(
     let temp_dir = tempfile::tempdir()?;
     let mount_path = temp_dir.path();
 
-    // Copy journal directory if it exists
+    // Copy the journal directory with its machine-ID subdirectories
     let journal_path = "/var/log/journal";
     if g.is_dir(journal_path).unwrap_or(false) {
-        let local_journal = mount_path.join("var/log/journal");
-        std::fs::create_dir_all(&local_journal)?;
+        let local_log = mount_path.join("var/log");
+        std::fs::create_dir_all(&local_log)?;
+        g.copy_out(journal_path, &local_log.to_string_lossy())
+            .with_context(|| format!("Failed to copy {}", journal_path))?;
+    }
+    g.umount_all().ok();
+    g.shutdown().ok();
 
-        if let Ok(entries) = g.ls(journal_path) {
-            for entry in entries {
-                let src = format!("{}/{}", journal_path, entry);
-                let dst = local_journal.join(&entry);
+    // Create analyzer and reader
+    let analyzer = SystemdAnalyzer::new(mount_path);
+    let reader = JournalReader::new(analyzer);
+
+    if verify {
+        let reports = reader.verify()?;
+        if reports.is_empty() {
+            println!("No journal files found");
+            return Ok(());
+        }
+
+        println!("{}", "Journal Verification".bold().underline());
+        println!();
+        let mut failed = false;
+        for report in &reports {
+            let name = report
+                .path
+                .strip_prefix(mount_path)
+                .unwrap_or(&report.path)
+                .display();
+            let Some(header) = &report.header else {
+                failed = true;
+                println!("{} {}", "FAIL".red(), name);
+                for problem in &report.problems {
+                    println!("     {}", problem);
+                }
+                continue;
+            };
 
-                if g.is_dir(&src).unwrap_or(false) {
-                    std::fs::create_dir_all(&dst)?;
-                } else if g.is_file(&src).unwrap_or(false) {
-                    if let Ok(content) = g.read_file(&src) {
-                        std::fs::write(&dst, content)?;
+            let mut problems = report.problems.clone();
+            if report.truncated {
+                problems.push(format!("Torn tail; file was left {}", header.state));
+            }
+            if report.undecoded_fields > 0 {
+                problems.push(format!(
+                    "{} fields could not be decompressed",
+                    report.undecoded_fields
+                ));
+            }
+            let mut seal = if report.seal.sealed {
+                format!(
+                    "sealed, {} tags, {} entries not sealed",
+                    report.seal.tags, report.seal.unsealed_entries
+                )
+            } else {
+                "not sealed".to_string()
+            };
+            if let (Some(key), true) = (verify_key, report.seal.sealed) {
+                match journal_file::verify_with_key(&report.path, key) {
+                    Ok((true, _)) => seal.push_str(", seals verified"),
+                    Ok((false, output)) => {
+                        problems.push(format!("Seal check failed: {}", output))
                     }
+                    Err(e) => problems.push(format!("Seal check not run: {}", e)),
                 }
             }
+
+            failed |= !problems.is_empty();
+            let status = if problems.is_empty() {
+                "PASS".green().to_string()
+            } else {
+                "FAIL".red().to_string()
+            };
+            println!("{} {}", status, name);
+            println!(
+                "     {} entries, {}, {}, {}",
+                report.entries,
+                header.state,
+                header.features().join(" "),
+                seal
+            );
+            for problem in &problems {
+                println!("     {}", problem);
+            }
+        }
+        if failed {
+            anyhow::bail!("Some journal files are damaged");
         }
+        return Ok(());
     }
 
-    // Create analyzer and reader
-    let analyzer = SystemdAnalyzer::new(mount_path);
-    let reader = JournalReader::new(analyzer);
+    if list_boots {
+        let boots = reader.list_boots()?;
+        if boots.is_empty() {
+            println!("No boots found in journal");
+        }
+        let time = |usec: u64| {
+            chrono::DateTime::from_timestamp((usec / 1_000_000) as i64, 0)
+                .map(|time| time.format("%Y-%m-%d %H:%M:%S").to_string())
+                .unwrap_or_default()
+        };
+        for boot in boots {
+            println!(
+                "{:>4} {} {} — {} ({} entries)",
+                boot.offset,
+                boot.boot_id,
+                time(boot.first),
+                time(boot.last),
+                boot.entries
+            );
+        }
+        return Ok(());
+    }
 
     if stats {
         // Show statistics
@@ -2185,6 +2640,7 @@ pub fn systemd_journal_command(
             priority,
             unit: unit.map(String::from),
             limit,
+            boot: boot.map(String::from),
             ..Default::default()
         };
 
@@ -2226,19 +2682,20 @@ pub fn systemd_journal_command(
         }
     } else {
         // Show journal entries
-        let entries = if errors {
-            reader.get_errors()?
-        } else if warnings {
-            reader.get_warnings()?
-        } else {
-            let filter = JournalFilter {
-                priority,
-                unit: unit.map(String::from),
-                limit,
-                ..Default::default()
-            };
-            reader.read_entries(&filter)?
+        let filter = JournalFilter {
+            priority: if errors {
+                Some(3)
+            } else if warnings {
+                Some(4)
+            } else {
+                priority
+            },
+            unit: unit.map(String::from),
+            limit,
+            boot: boot.map(String::from),
+            ..Default::default()
         };
+        let entries = reader.read_entries(&filter)?;
 
         if entries.is_empty() {
             println!("No journal entries found");
@@ -2268,8 +2725,6 @@ pub fn systemd_journal_command(
         }
     }
 
-    g.umount_all().ok();
-    g.shutdown().ok();
     Ok(())
 }
 
@@ -2353,7 +2808,7 @@ pub fn systemd_services_command(
             println!();
 
             for service in failed_services {
-                println!("{} {}", "✗".red(), service.name.bright_red());
+                println!("{} {}", plain::text("✗").red(), service.name.bright_red());
                 if let Some(desc) = service.description {
                     println!("  Description: {}", desc.dimmed());
                 }
@@ -2446,9 +2901,9 @@ pub fn systemd_boot_command(
 
         for rec in recs {
             if rec.contains("looks good") {
-                println!("{} {}", "✓".green(), rec.green());
+                println!("{} {}", plain::text("✓").green(), rec.green());
             } else {
-                println!("{} {}", "⚠".yellow(), rec);
+                println!("{} {}", plain::text("⚠").yellow(), rec);
             }
         }
     } else if summary {
@@ -2499,102 +2954,218 @@ pub fn systemd_boot_command(
     Ok(())
 }
 
-/// Enhanced cat with line numbers and special character display
-pub fn cat_file_enhanced(
-    image: &PathBuf,
-    path: &str,
-    line_numbers: bool,
-    show_all: bool,
+/// Check recorded boot timings against a boot time budget
+pub fn systemd_boot_budget_command(
+    image: &Path,
+    budget_path: &Path,
+    output: Option<&str>,
     verbose: bool,
 ) -> Result<()> {
-    use guestkit::core::ProgressReporter;
-    use guestkit::Guestfs;
+    let budget = BootBudget::load(budget_path)?;
+    let (mut g, _root) = mount_disk_for_systemd(image, verbose)?;
 
-    let mut g = Guestfs::new().context("Failed to create Guestfs handle")?;
+    // Budgets are only meaningful against measured timings, not estimates
+    let temp_dir = tempfile::tempdir()?;
+    let local_dir = temp_dir.path().join("var/lib/systemd");
+    std::fs::create_dir_all(&local_dir)?;
 
-    if verbose {
-        g.set_verbose(true);
+    let blame_path = "/var/lib/systemd/analyze-blame.txt";
+    if !g.is_file(blame_path).unwrap_or(false) {
+        g.shutdown().ok();
+        anyhow::bail!(
+            "No recorded boot timings in image (save `systemd-analyze blame` to {})",
+            blame_path
+        );
+    }
+    for name in ["analyze-blame.txt", "analyze-time.txt"] {
+        let path = format!("/var/lib/systemd/{}", name);
+        if g.is_file(&path).unwrap_or(false) {
+            std::fs::write(local_dir.join(name), g.read_file(&path)?)?;
+        }
     }
 
-    let progress = ProgressReporter::spinner("Loading disk image...");
-
-    g.add_drive_ro(image.to_str().unwrap())
-        .with_context(|| format!("Failed to add disk: {}", image.display()))?;
+    g.umount_all().ok();
+    g.shutdown().ok();
 
-    progress.set_message("Launching appliance...");
-    g.launch().context("Failed to launch appliance")?;
+    let timing = BootAnalyzer::new(SystemdAnalyzer::new(temp_dir.path())).analyze_boot()?;
+    let report = budget.evaluate(&timing);
 
-    // Mount filesystems
-    progress.set_message("Mounting filesystems...");
+    if output == Some("json") {
+        println!("{}", serde_json::to_string_pretty(&report)?);
+    } else {
+        print_budget_report(&report, &budget);
+    }
 
-    let roots = g.inspect_os().unwrap_or_default();
-    if !roots.is_empty() {
-        let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
+    if !report.passed() {
+        eprintln!(
+            "{} Boot budget exceeded: {} violations",
+            plain::text("❌"),
+            report.violations.len()
+        );
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+fn print_budget_report(report: &BudgetReport, budget: &BootBudget) {
+    let secs = |ms: u64| format!("{:.2}s", ms as f64 / 1000.0);
+
+    println!("{}", "Boot Budget Check".bold().underline());
+    println!();
+    match budget.total {
+        Some(total) => println!(
+            "Total Boot Time: {} (budget {})",
+            secs(report.total),
+            secs(total)
+        ),
+        None => println!("Total Boot Time: {}", secs(report.total)),
+    }
+    println!();
+
+    for violation in &report.violations {
+        let name = violation.unit.as_deref().unwrap_or(&violation.scope);
+        println!(
+            "{} {:<50} {} > {} ({})",
+            plain::text("✗").red(),
+            name,
+            secs(violation.actual).red(),
+            secs(violation.budget),
+            format!("+{}", secs(violation.excess())).red()
+        );
+    }
+    for scope in &report.unchecked {
+        println!(
+            "{} {} budget not checked: no {} timing recorded (save `systemd-analyze time` to /var/lib/systemd/analyze-time.txt)",
+            plain::text("⚠").yellow(),
+            scope,
+            scope
+        );
+    }
+
+    if !report.candidates.is_empty() {
+        println!();
+        println!("{}", "Candidates to mask or disable:".bold());
+        for candidate in &report.candidates {
+            println!(
+                "  systemctl {} {:<40} saves {} - {}",
+                candidate.action,
+                candidate.unit.bright_blue(),
+                secs(candidate.saves),
+                candidate.reason.dimmed()
+            );
         }
     }
 
+    println!();
+    if report.passed() {
+        println!("{} {}", plain::text("✓").green(), "Boot time within budget".green());
+    }
+}
+
+/// Enhanced cat with line numbers and special character display
+pub fn cat_file_enhanced(
+    image: &PathBuf,
+    path: &str,
+    line_numbers: bool,
+    show_all: bool,
+    max_bytes: u64,
+    verbose: bool,
+) -> Result<()> {
+    use guestkit::core::ProgressReporter;
+    use guestkit::Guestfs;
+    use std::io::{Read, Write};
+
+    let mut g = Guestfs::new().context("Failed to create Guestfs handle")?;
+
+    if verbose {
+        g.set_verbose(true);
+    }
+
+    let progress = ProgressReporter::spinner("Loading disk image...");
+
+    g.add_drive_ro(image.to_str().unwrap())
+        .with_context(|| format!("Failed to add disk: {}", image.display()))?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
+
+    progress.set_message("Launching appliance...");
+    g.launch().context("Failed to launch appliance")?;
+
+    // Mount filesystems
+    progress.set_message("Mounting filesystems...");
+
+    let roots = target::os_roots(&mut g)?;
+    if !roots.is_empty() {
+        let root = &roots[0];
+        g.mount_os_ro(root).ok();
+    }
+
     // Check if file exists
     if !g.is_file(path).unwrap_or(false) {
         progress.abandon_with_message(format!("File not found: {}", path));
         anyhow::bail!("File not found: {}", path);
     }
 
-    // Read and print file
+    // Read and print file, streaming so large files never sit in memory
     progress.set_message(format!("Reading {}...", path));
-    let content = g
-        .read_file(path)
+    let size = g
+        .filesize(path)
+        .with_context(|| format!("Failed to read file: {}", path))? as u64;
+    let binary = g
+        .is_binary_file(path)
         .with_context(|| format!("Failed to read file: {}", path))?;
 
     progress.finish_and_clear();
 
-    // Try to print as UTF-8
-    match String::from_utf8(content.clone()) {
-        Ok(text) => {
-            for (idx, line) in text.lines().enumerate() {
-                let display_line = if show_all {
-                    // Show special characters
-                    line.replace('\t', "^I")
-                        .replace('\r', "^M")
-                        .chars()
-                        .map(|c| {
-                            if c.is_control() {
-                                format!("^{}", (c as u8 + 64) as char)
-                            } else {
-                                c.to_string()
-                            }
-                        })
-                        .collect::<String>()
-                } else {
-                    line.to_string()
-                };
+    if binary {
+        eprintln!("Warning: File contains binary data");
+    }
 
-                if line_numbers {
-                    println!("{:6}\t{}", idx + 1, display_line);
-                } else {
-                    println!("{}", display_line);
-                }
-            }
+    let mut out = std::io::stdout().lock();
+    if max_bytes == 0 || size <= max_bytes {
+        let reader = g
+            .open_file(path)
+            .with_context(|| format!("Failed to read file: {}", path))?;
+        if binary {
+            preview::write_hex(reader, &mut out, 0, line_numbers)?;
+        } else {
+            preview::write_lines(reader, &mut out, 1, line_numbers, show_all)?;
         }
-        Err(_) => {
-            eprintln!("Warning: File contains binary data");
-            // Print hex dump
-            for (i, chunk) in content.chunks(16).enumerate() {
-                if line_numbers {
-                    print!("{:08x}: ", i * 16);
-                }
-                for byte in chunk {
-                    print!("{:02x} ", byte);
-                }
-                println!();
-            }
+    } else {
+        // Too large: show the start and end of the file
+        let window = g
+            .preview_file(path, max_bytes)
+            .with_context(|| format!("Failed to read file: {}", path))?;
+        if binary {
+            preview::write_hex(&window.head[..], &mut out, 0, line_numbers)?;
+            out.flush()?;
+            eprintln!("{}", preview::omitted_notice(&window));
+            let tail_offset = size - window.tail.len() as u64;
+            preview::write_hex(&window.tail[..], &mut out, tail_offset, line_numbers)?;
+        } else {
+            preview::write_lines(&window.head[..], &mut out, 1, line_numbers, show_all)?;
+            out.flush()?;
+            eprintln!("{}", preview::omitted_notice(&window));
+
+            // Tail line numbers need a count of the lines that were skipped
+            let first_tail_line = if line_numbers {
+                let skipped = g.open_file(path)?.take(size - window.tail.len() as u64);
+                preview::count_lines(skipped)? + 1
+            } else {
+                1
+            };
+            preview::write_lines(
+                &window.tail[..],
+                &mut out,
+                first_tail_line,
+                line_numbers,
+                show_all,
+            )?;
         }
     }
+    out.flush()?;
+    drop(out);
 
     g.umount_all().ok();
     g.shutdown().ok();
@@ -2619,22 +3190,18 @@ pub fn hash_command(
     let progress = ProgressReporter::spinner("Loading disk image...");
 
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
-        }
+        g.mount_os_ro(root).ok();
     }
 
     progress.set_message(format!("Computing {} hash...", algorithm));
@@ -2662,9 +3229,9 @@ pub fn hash_command(
 
         if let Some(expected) = check {
             if hash.to_lowercase() == expected.to_lowercase() {
-                println!("✓ Hash verified: {}: OK", path);
+                println!("{} Hash verified: {}: OK", plain::text("✓"), path);
             } else {
-                eprintln!("✗ Hash mismatch!");
+                eprintln!("{} Hash mismatch!", plain::text("✗"));
                 eprintln!("  Expected: {}", expected);
                 eprintln!("  Got:      {}", hash);
                 anyhow::bail!("Hash verification failed");
@@ -2690,54 +3257,51 @@ pub fn search_command(
     file_type: Option<String>,
     max_depth: Option<usize>,
     limit: Option<usize>,
+    exclude: Vec<String>,
+    exclude_from: Option<PathBuf>,
     verbose: bool,
 ) -> Result<()> {
+    use super::matcher::{glob_to_regex, NameMatcher, PathFilter, PatternSyntax};
     use guestkit::core::ProgressReporter;
     use guestkit::Guestfs;
     use regex::RegexBuilder;
 
+    let syntax = if regex { PatternSyntax::Regex } else { PatternSyntax::Glob };
+    let name_matcher = NameMatcher::new(pattern, syntax, ignore_case)?;
+    // Content is searched for the pattern anywhere in the text
+    let content_re = if content {
+        let source = match syntax {
+            PatternSyntax::Glob => glob_to_regex(pattern)?,
+            PatternSyntax::Regex => pattern.to_string(),
+        };
+        Some(RegexBuilder::new(&source).case_insensitive(ignore_case).build()?)
+    } else {
+        None
+    };
+    let filter = PathFilter::new(&[], &exclude, exclude_from.as_deref(), ignore_case)?;
+
     let mut g = Guestfs::new()?;
     g.set_verbose(verbose);
 
     let progress = ProgressReporter::spinner("Loading disk image...");
 
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
-        }
+        g.mount_os_ro(root).ok();
     }
 
     progress.set_message(format!("Searching for '{}'...", pattern));
 
-    // Convert glob to regex if needed
-    let pattern_re = if regex {
-        RegexBuilder::new(pattern)
-            .case_insensitive(ignore_case)
-            .build()?
-    } else {
-        // Convert glob to regex
-        let regex_pattern = pattern
-            .replace(".", r"\.")
-            .replace("*", ".*")
-            .replace("?", ".");
-        RegexBuilder::new(&regex_pattern)
-            .case_insensitive(ignore_case)
-            .build()?
-    };
-
     // Find all files
     let all_files = g.find(search_path)?;
 
@@ -2761,6 +3325,11 @@ pub fn search_command(
             }
         }
 
+        // Skip excluded paths
+        if !filter.is_empty() && filter.is_excluded(&file, g.is_dir(&file).unwrap_or(false)) {
+            continue;
+        }
+
         // Check file type
         if let Some(ref ftype) = file_type {
             let is_dir = g.is_dir(&file).unwrap_or(false);
@@ -2789,14 +3358,14 @@ pub fn search_command(
 
         // Name matching
         let file_name = file.rsplit('/').next().unwrap_or(&file);
-        let name_matches = pattern_re.is_match(file_name);
+        let name_matches = name_matcher.is_match(file_name);
 
-        if content {
+        if let Some(ref content_re) = content_re {
             // Content search
             if g.is_file(&file).unwrap_or(false) {
                 if let Ok(file_content) = g.read_file(&file) {
                     if let Ok(text) = String::from_utf8(file_content) {
-                        if pattern_re.is_match(&text) {
+                        if content_re.is_match(&text) {
                             matches.push(file.clone());
                             count += 1;
                         }
@@ -2828,47 +3397,77 @@ pub fn search_command(
     Ok(())
 }
 
+/// Options for [`list_files_enhanced`]
+#[derive(Debug, Clone, Default)]
+pub struct ListOptions {
+    /// List subdirectories too
+    pub recursive: bool,
+    /// Show permissions, size and owner
+    pub long: bool,
+    /// Show hidden files
+    pub all: bool,
+    /// Human-readable file sizes
+    pub human_readable: bool,
+    /// Sort by modification time
+    pub sort_time: bool,
+    pub reverse: bool,
+    /// Only list names matching this glob
+    pub filter: Option<String>,
+    pub directories_only: bool,
+    /// Stop after this many entries
+    pub limit: Option<usize>,
+    /// Gitignore-style exclude patterns
+    pub exclude: Vec<String>,
+    /// File of gitignore-style exclude patterns
+    pub exclude_from: Option<PathBuf>,
+    /// Match the filter and exclude patterns case-insensitively
+    pub ignore_case: bool,
+}
+
 /// Enhanced list files with comprehensive options
 pub fn list_files_enhanced(
     image: &PathBuf,
     path: &str,
-    recursive: bool,
-    long: bool,
-    all: bool,
-    human_readable: bool,
-    sort_time: bool,
-    reverse: bool,
-    filter: Option<String>,
-    directories_only: bool,
-    limit: Option<usize>,
+    options: ListOptions,
     verbose: bool,
 ) -> Result<()> {
+    use super::matcher::{NameMatcher, PathFilter, PatternSyntax};
     use guestkit::core::ProgressReporter;
     use guestkit::Guestfs;
-    use regex::Regex;
     use chrono::{Utc, TimeZone};
 
+    let ListOptions {
+        recursive,
+        long,
+        all,
+        human_readable,
+        sort_time,
+        reverse,
+        filter,
+        directories_only,
+        limit,
+        exclude,
+        exclude_from,
+        ignore_case,
+    } = options;
+
     let mut g = Guestfs::new()?;
     g.set_verbose(verbose);
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
-        }
+        g.mount_os_ro(root).ok();
     }
 
     progress.set_message(format!("Listing {}...", path));
@@ -2891,11 +3490,11 @@ pub fn list_files_enhanced(
     };
 
     // Apply filter
-    let filter_re = if let Some(ref pattern) = filter {
-        Some(Regex::new(&pattern.replace("*", ".*").replace("?", "."))?)
-    } else {
-        None
-    };
+    let name_filter = filter
+        .as_deref()
+        .map(|pattern| NameMatcher::new(pattern, PatternSyntax::Glob, ignore_case))
+        .transpose()?;
+    let path_filter = PathFilter::new(&[], &exclude, exclude_from.as_deref(), ignore_case)?;
 
     for file_path in files_to_list {
         // Skip hidden files unless -a
@@ -2907,9 +3506,9 @@ pub fn list_files_enhanced(
         }
 
         // Apply filter
-        if let Some(ref re) = filter_re {
+        if let Some(ref matcher) = name_filter {
             let file_name = file_path.rsplit('/').next().unwrap_or(&file_path);
-            if !re.is_match(file_name) {
+            if !matcher.is_match(file_name) {
                 continue;
             }
         }
@@ -2917,6 +3516,10 @@ pub fn list_files_enhanced(
         if let Ok(stat) = g.lstat(&file_path) {
             let is_dir = (stat.mode & 0o170000) == 0o040000;
 
+            if path_filter.is_excluded(&file_path, is_dir) {
+                continue;
+            }
+
             // Filter directories only
             if directories_only && !is_dir {
                 continue;
@@ -3013,6 +3616,32 @@ fn format_size(size: u64) -> String {
     }
 }
 
+/// Set the modification time of an extracted file, if the host allows it
+fn set_mtime(path: &Path, mtime: i64) {
+    use std::time::{Duration, UNIX_EPOCH};
+
+    if let Ok(mtime) = u64::try_from(mtime) {
+        if let Ok(file) = std::fs::File::options().write(true).open(path) {
+            file.set_modified(UNIX_EPOCH + Duration::from_secs(mtime)).ok();
+        }
+    }
+}
+
+/// Carry the xattrs, SELinux label, ACLs and capabilities of a guest file
+/// over to the host; the host filesystem or privileges may not allow all
+/// of them, so failures only warn
+fn preserve_security_attrs(g: &mut Guestfs, guest_path: &str, host_path: &Path) {
+    let result = g
+        .get_security_attrs(guest_path)
+        .and_then(|attrs| attrs.apply_to_host(host_path));
+    if let Err(e) = result {
+        eprintln!(
+            "Warning: could not preserve security attributes of {}: {}",
+            guest_path, e
+        );
+    }
+}
+
 /// Enhanced extract with recursive, preserve, and verification
 pub fn extract_file_enhanced(
     image: &PathBuf,
@@ -3035,22 +3664,18 @@ pub fn extract_file_enhanced(
 
     let prog = ProgressReporter::spinner("Loading disk image...");
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     prog.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     prog.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
-        }
+        g.mount_os_ro(root).ok();
     }
 
     // Check if source exists
@@ -3081,6 +3706,9 @@ pub fn extract_file_enhanced(
 
             if g.is_dir(&file_path).unwrap_or(false) {
                 fs::create_dir_all(&target_path)?;
+                if preserve {
+                    preserve_security_attrs(&mut g, &file_path, &target_path);
+                }
             } else if g.is_file(&file_path).unwrap_or(false) {
                 // Check if file exists
                 if target_path.exists() && !force {
@@ -3100,9 +3728,11 @@ pub fn extract_file_enhanced(
                     file_count += 1;
 
                     if preserve {
-                        // Set permissions
+                        // Before permissions, which may make it read-only
+                        set_mtime(&target_path, stat.mtime);
                         let perms = fs::Permissions::from_mode(stat.mode as u32 & 0o777);
                         fs::set_permissions(&target_path, perms).ok();
+                        preserve_security_attrs(&mut g, &file_path, &target_path);
                     }
                 }
 
@@ -3125,8 +3755,10 @@ pub fn extract_file_enhanced(
             file_count = 1;
 
             if preserve {
+                set_mtime(host_path, stat.mtime);
                 let perms = fs::Permissions::from_mode(stat.mode as u32 & 0o777);
                 fs::set_permissions(host_path, perms).ok();
+                preserve_security_attrs(&mut g, guest_path, host_path);
             }
         }
     }
@@ -3134,7 +3766,8 @@ pub fn extract_file_enhanced(
     prog.finish_and_clear();
 
     println!(
-        "✓ Extracted {} file(s), {} total",
+        "{} Extracted {} file(s), {} total",
+        plain::text("✓"),
         file_count,
         format_size(total_bytes)
     );
@@ -3143,7 +3776,7 @@ pub fn extract_file_enhanced(
     if verify {
         println!("Verifying extracted files...");
         // Simple size check for now
-        println!("✓ Verification complete");
+        println!("{} Verification complete", plain::text("✓"));
     }
 
     g.umount_all().ok();
@@ -3151,24 +3784,51 @@ pub fn extract_file_enhanced(
     Ok(())
 }
 
+/// Options for [`grep_command`]
+#[derive(Debug, Clone, Default)]
+pub struct GrepCommandOptions {
+    pub ignore_case: bool,
+    pub line_numbers: bool,
+    /// Search every file under the path
+    pub recursive: bool,
+    /// Print only the names of matching files
+    pub files_only: bool,
+    /// Select non-matching lines
+    pub invert: bool,
+    /// Context lines before each match
+    pub before_context: Option<usize>,
+    /// Context lines after each match
+    pub after_context: Option<usize>,
+    /// Stop after this many matches across all files
+    pub max_count: Option<usize>,
+    /// Search at most this many bytes of each file (0 = no limit)
+    pub max_bytes: u64,
+}
+
 /// Search file contents like grep
 pub fn grep_command(
     image: &PathBuf,
     pattern: &str,
     search_path: &str,
-    ignore_case: bool,
-    line_numbers: bool,
-    recursive: bool,
-    files_only: bool,
-    invert: bool,
-    before_context: Option<usize>,
-    after_context: Option<usize>,
-    max_count: Option<usize>,
+    options: &GrepCommandOptions,
     verbose: bool,
 ) -> Result<()> {
     use guestkit::core::ProgressReporter;
     use guestkit::Guestfs;
     use regex::RegexBuilder;
+    use std::io::Read;
+
+    let GrepCommandOptions {
+        ignore_case,
+        line_numbers,
+        recursive,
+        files_only,
+        invert,
+        before_context,
+        after_context,
+        max_count,
+        max_bytes,
+    } = *options;
 
     let mut g = Guestfs::new()?;
     g.set_verbose(verbose);
@@ -3176,22 +3836,18 @@ pub fn grep_command(
     let progress = ProgressReporter::spinner("Loading disk image...");
 
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
-        }
+        g.mount_os_ro(root).ok();
     }
 
     progress.set_message(format!("Searching for '{}'...", pattern));
@@ -3219,85 +3875,82 @@ pub fn grep_command(
     let mut total_matches = 0;
 
     for file in files_to_search {
-        if let Some(max) = max_count {
-            if total_matches >= max {
-                break;
-            }
-        }
-
-        if let Ok(content_bytes) = g.read_file(&file) {
-            if let Ok(content) = String::from_utf8(content_bytes) {
-                let lines: Vec<&str> = content.lines().collect();
-                let mut file_had_match = false;
-                let mut match_lines = Vec::new();
-
-                for (line_no, line) in lines.iter().enumerate() {
-                    let matches = pattern_re.is_match(line);
-                    let should_print = if invert { !matches } else { matches };
-
-                    if should_print {
-                        file_had_match = true;
-                        total_matches += 1;
-
-                        if !files_only {
-                            // Calculate context range
-                            let start = if let Some(before) = before_context {
-                                line_no.saturating_sub(before)
-                            } else {
-                                line_no
-                            };
+        let remaining = match max_count {
+            Some(max) if total_matches >= max => break,
+            Some(max) => Some(max - total_matches),
+            None => None,
+        };
 
-                            let end = if let Some(after) = after_context {
-                                (line_no + after + 1).min(lines.len())
-                            } else {
-                                line_no + 1
-                            };
+        let binary = match g.is_binary_file(&file) {
+            Ok(binary) => binary,
+            Err(_) => continue,
+        };
+        let reader = match g.open_file(&file) {
+            Ok(reader) => reader.take(preview::read_limit(max_bytes)),
+            Err(_) => continue,
+        };
+        if max_bytes > 0 && g.filesize(&file).unwrap_or(0) as u64 > max_bytes {
+            eprintln!(
+                "{}: searching only the first {} (--max-bytes)",
+                file,
+                format_size(max_bytes)
+            );
+        }
 
-                            // Add context lines
-                            for i in start..end {
-                                match_lines.push((i, lines[i], i == line_no));
-                            }
-                        }
+        // Binary files and -l only need to know whether anything matches
+        let presence_only = binary || files_only;
+        let options = if presence_only {
+            preview::GrepOptions {
+                invert,
+                limit: Some(1),
+                ..Default::default()
+            }
+        } else {
+            preview::GrepOptions {
+                invert,
+                before: before_context.unwrap_or(0),
+                after: after_context.unwrap_or(0),
+                limit: remaining,
+            }
+        };
 
-                        if let Some(max) = max_count {
-                            if total_matches >= max {
-                                break;
-                            }
-                        }
-                    }
+        let mut header_printed = false;
+        let matches = preview::grep_lines(reader, &pattern_re, &options, |line| {
+            if presence_only {
+                return Ok(());
+            }
+            // Print file header for multiple files
+            if recursive && !header_printed {
+                println!("{}:", file);
+                header_printed = true;
+            }
+            if line_numbers {
+                if line.is_match {
+                    println!("{}: {}", line.line_no + 1, line.text);
+                } else {
+                    println!("{}- {}", line.line_no + 1, line.text);
                 }
+            } else {
+                println!("{}", line.text);
+            }
+            Ok(())
+        });
+        let matches = match matches {
+            Ok(matches) => matches,
+            Err(_) => continue,
+        };
 
-                if file_had_match {
-                    if files_only {
-                        println!("{}", file);
-                    } else {
-                        // Print file header for multiple files
-                        if recursive {
-                            println!("{}:", file);
-                        }
-
-                        // Deduplicate context lines
-                        match_lines.sort_by_key(|(line_no, _, _)| *line_no);
-                        match_lines.dedup_by_key(|(line_no, _, _)| *line_no);
-
-                        for (line_no, line, is_match) in match_lines {
-                            if line_numbers {
-                                if is_match {
-                                    println!("{}: {}", line_no + 1, line);
-                                } else {
-                                    println!("{}- {}", line_no + 1, line);
-                                }
-                            } else {
-                                println!("{}", line);
-                            }
-                        }
+        if matches == 0 {
+            continue;
+        }
+        total_matches += matches;
 
-                        if recursive {
-                            println!();
-                        }
-                    }
-                }
-            }
+        if files_only {
+            println!("{}", file);
+        } else if binary {
+            println!("Binary file {} matches", file);
+        } else if recursive {
+            println!();
         }
     }
 
@@ -3317,42 +3970,172 @@ pub fn scan_command(
     _output: Option<String>,
     report: bool,
     check_cve: bool,
+    vex: &[PathBuf],
     verbose: bool,
 ) -> Result<()> {
     use guestkit::core::ProgressReporter;
     use guestkit::Guestfs;
 
+    // Vulnerabilities come from the database `guestctl vulndb update` builds
+    let db = if check_cve { Some(VulnDb::open()?) } else { None };
+    let vex = Vex::load(vex)?;
+
     let mut g = Guestfs::new()?;
     g.set_verbose(verbose);
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
-    // Mount filesystems
-    progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
-    if !roots.is_empty() {
-        let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
+    // Scan every operating system, or only the one chosen with --os-root
+    let roots = g.inspect_os_selected().unwrap_or_default();
+    let mut results = Vec::new();
+    for root in &roots {
+        // Mount filesystems
+        progress.set_message("Mounting filesystems...");
+        g.umount_all().ok();
+        g.mount_os_ro(root).ok();
+
+        progress.set_message(format!("Scanning for {} vulnerabilities...", scan_type));
+        let findings = scan_root(&mut g, root, scan_type);
+
+        let cves = db.as_ref().map(|db| {
+            progress.set_message("Checking packages for known CVEs...");
+            package_cves(&mut g, root, db).map(|mut cves| {
+                if let Some(min) = &severity {
+                    cves.retain(|c| {
+                        cve::severity_rank(&c.vuln.severity) >= cve::severity_rank(min)
+                    });
+                }
+                let found = cves.len();
+                cves.retain(|c| !vex.suppresses(&c.vuln.cve, &c.purl));
+                let suppressed = found - cves.len();
+                (cves, suppressed)
+            })
+        });
+        results.push((root, findings, cves));
+    }
+
+    progress.finish_and_clear();
+
+    // Display results
+    println!("Security Scan Results");
+    println!("=====================");
+    println!("Scan type: {}", scan_type);
+    if let Some(ref sev) = severity {
+        println!("Severity threshold: {}", sev);
+    }
+    println!();
+
+    if results.is_empty() {
+        println!("No operating systems found");
+    }
+    for (root, findings, cves) in results {
+        if roots.len() > 1 {
+            println!("OS root: {}", root);
+        }
+        if findings.is_empty() {
+            println!("No issues found");
+        } else {
+            println!("Found {} potential issues:", findings.len());
+            for finding in findings {
+                println!("  {} {}", plain::text("•"), finding);
+            }
+        }
+        match cves {
+            None => {}
+            Some(None) => println!("CVE check skipped: no vulnerability data for this release"),
+            Some(Some((cves, suppressed))) => {
+                if cves.is_empty() {
+                    println!("No known vulnerabilities");
+                } else {
+                    println!("Found {} known vulnerabilities:", cves.len());
+                }
+                for PackageCve { app, vuln, .. } in cves {
+                    let fix = vuln
+                        .fixed_version
+                        .as_deref()
+                        .map(|fixed| format!(", fixed in {}", fixed))
+                        .unwrap_or_default();
+                    println!(
+                        "  {} {} [{}] {} {}{}",
+                        plain::text("•"),
+                        vuln.cve,
+                        vuln.severity,
+                        app.name,
+                        app.full_version(),
+                        fix
+                    );
+                }
+                if suppressed > 0 {
+                    println!("{} more marked not affected or fixed by VEX", suppressed);
+                }
             }
         }
+        if roots.len() > 1 {
+            println!();
+        }
     }
 
-    progress.set_message(format!("Scanning for {} vulnerabilities...", scan_type));
+    if report {
+        println!();
+        println!("Detailed report generation not yet implemented");
+    }
+
+    g.umount_all().ok();
+    g.shutdown().ok();
+    Ok(())
+}
+
+/// A known vulnerability of an installed package
+struct PackageCve {
+    app: Application,
+    /// Package URL, as VEX documents name the package
+    purl: String,
+    vuln: VulnerabilityInfo,
+}
+
+/// Known vulnerabilities of the packages of the mounted OS, or `None` if
+/// the database does not cover its release
+fn package_cves(g: &mut Guestfs, root: &str, db: &VulnDb) -> Option<Vec<PackageCve>> {
+    let cves = CveLookup::for_os(db, g, root)?;
+    let format = g.inspect_get_package_format(root).unwrap_or_default();
+    // Named by their package URL types
+    let package_type = if format == "pacman" { "alpm" } else { format.as_str() };
+    let distro = g.inspect_get_distro(root).unwrap_or_default();
+
+    let mut found = Vec::new();
+    for app in g.inspect_list_applications(root).unwrap_or_default() {
+        let purl = vex::package_purl(
+            package_type,
+            &distro,
+            &app.name,
+            &app.full_version(),
+            &app.arch,
+        );
+        for vuln in cves.lookup(&app) {
+            found.push(PackageCve {
+                app: app.clone(),
+                purl: purl.clone(),
+                vuln,
+            });
+        }
+    }
+    Some(found)
+}
 
+/// Findings of a security scan of the mounted OS
+fn scan_root(g: &mut Guestfs, root: &str, scan_type: &str) -> Vec<String> {
     let mut findings = Vec::new();
 
     // Scan based on type
     if scan_type == "packages" || scan_type == "all" {
         // Check for outdated or vulnerable packages
-        if let Ok(apps) = g.inspect_list_applications(&roots[0]) {
+        if let Ok(apps) = g.inspect_list_applications(root) {
             for app in apps.iter().take(10) {
                 // Simplified: just list some packages
                 findings.push(format!(
@@ -3401,40 +4184,8 @@ pub fn scan_command(
         }
     }
 
-    progress.finish_and_clear();
-
-    // Display results
-    println!("Security Scan Results");
-    println!("=====================");
-    println!("Scan type: {}", scan_type);
-    if let Some(ref sev) = severity {
-        println!("Severity threshold: {}", sev);
-    }
-    println!();
-
-    if findings.is_empty() {
-        println!("No issues found");
-    } else {
-        println!("Found {} potential issues:", findings.len());
-        for finding in findings {
-            println!("  • {}", finding);
-        }
-    }
-
-    if check_cve {
-        println!();
-        println!("Note: CVE database checking not yet implemented");
-    }
-
-    if report {
-        println!();
-        println!("Detailed report generation not yet implemented");
-    }
-
-    g.umount_all().ok();
-    g.shutdown().ok();
-    Ok(())
-}
+    findings
+}
 
 /// Benchmark disk I/O performance
 pub fn benchmark_command(
@@ -3454,22 +4205,18 @@ pub fn benchmark_command(
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
-        }
+        g.mount_os_ro(root).ok();
     }
 
     progress.finish_and_clear();
@@ -3532,102 +4279,138 @@ pub fn snapshot_command(
     description: Option<String>,
     _verbose: bool,
 ) -> Result<()> {
-    use guestkit::core::ProgressReporter;
+    use super::audit_log::audited;
+    use guestkit::disk::qcow2::{Qcow2Image, SnapshotInfo};
+    use serde_json::json;
 
-    let msg = format!("Snapshot operation: {}...", operation);
-    let progress = ProgressReporter::spinner(&msg);
+    let required = |name: Option<String>| {
+        name.with_context(|| format!("Snapshot name required for {}; use --name", operation))
+    };
+    let created = |info: &SnapshotInfo| {
+        chrono::DateTime::from_timestamp(i64::from(info.date_sec), info.date_nsec)
+            .map(|date| date.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_default()
+    };
 
     match operation {
         "create" => {
             let snap_name = name.unwrap_or_else(|| {
-                chrono::Utc::now().format("snapshot-%Y%m%d-%H%M%S").to_string()
+                chrono::Utc::now()
+                    .format("snapshot-%Y%m%d-%H%M%S")
+                    .to_string()
             });
+            let parameters = json!({ "snapshot": snap_name, "description": description });
+            let info = audited("snapshot-create", image, parameters, || {
+                Qcow2Image::open_rw(image)
+                    .and_then(|mut qcow2| qcow2.create_snapshot(&snap_name))
+                    .with_context(|| {
+                        format!(
+                            "Failed to create snapshot '{}' of {}",
+                            snap_name,
+                            image.display()
+                        )
+                    })
+            })?;
 
-            progress.set_message(format!("Creating snapshot '{}'...", snap_name));
-
-            // In a real implementation, this would create a QCOW2 snapshot
-            // or use libvirt snapshot APIs
-
-            progress.finish_and_clear();
-
-            println!("✓ Created snapshot: {}", snap_name);
+            println!("{} Created snapshot: {} (ID {})", plain::text("✓"), info.name, info.id);
+            println!("  Image: {}", image.display());
             if let Some(desc) = description {
-                println!("  Description: {}", desc);
+                // qcow2 snapshots have no description field
+                println!("  Description (audit log only): {}", desc);
             }
-            println!("  Image: {}", image.display());
-            println!();
-            println!("Note: Snapshot creation not fully implemented yet");
-            println!("      Would create QCOW2 internal snapshot or use qemu-img");
         }
 
         "list" => {
-            progress.set_message("Listing snapshots...");
-
-            progress.finish_and_clear();
+            let snapshots = Qcow2Image::open(image)
+                .and_then(|qcow2| qcow2.snapshots())
+                .with_context(|| format!("Failed to read the snapshots of {}", image.display()))?;
 
             println!("Snapshots for {}:", image.display());
             println!();
-            println!("Note: Snapshot listing not fully implemented yet");
-            println!("      Would use qemu-img snapshot -l or libvirt APIs");
+            if snapshots.is_empty() {
+                println!("No snapshots");
+                return Ok(());
+            }
+            println!(
+                "{:<6} {:<30} {:>10} {:<19}",
+                "ID", "NAME", "VM STATE", "DATE"
+            );
+            for info in &snapshots {
+                println!(
+                    "{:<6} {:<30} {:>10} {:<19}",
+                    info.id,
+                    info.name,
+                    format_size(info.vm_state_size),
+                    created(info)
+                );
+            }
         }
 
         "delete" => {
-            if let Some(snap_name) = name {
-                progress.set_message(format!("Deleting snapshot '{}'...", snap_name));
-
-                progress.finish_and_clear();
-
-                println!("✓ Deleted snapshot: {}", snap_name);
-                println!();
-                println!("Note: Snapshot deletion not fully implemented yet");
-                println!("      Would use qemu-img snapshot -d");
-            } else {
-                progress.abandon_with_message("Snapshot name required for delete operation");
-                anyhow::bail!("Please provide snapshot name with --name");
-            }
+            let snap_name = required(name)?;
+            audited(
+                "snapshot-delete",
+                image,
+                json!({ "snapshot": snap_name }),
+                || {
+                    Qcow2Image::open_rw(image)
+                        .and_then(|mut qcow2| qcow2.delete_snapshot(&snap_name))
+                        .with_context(|| {
+                            format!(
+                                "Failed to delete snapshot '{}' of {}",
+                                snap_name,
+                                image.display()
+                            )
+                        })
+                },
+            )?;
+            println!("{} Deleted snapshot: {}", plain::text("✓"), snap_name);
         }
 
         "revert" => {
-            if let Some(snap_name) = name {
-                progress.set_message(format!("Reverting to snapshot '{}'...", snap_name));
-
-                progress.finish_and_clear();
-
-                println!("✓ Reverted to snapshot: {}", snap_name);
-                println!();
-                println!("Note: Snapshot revert not fully implemented yet");
-                println!("      Would use qemu-img snapshot -a");
-            } else {
-                progress.abandon_with_message("Snapshot name required for revert operation");
-                anyhow::bail!("Please provide snapshot name with --name");
-            }
+            let snap_name = required(name)?;
+            audited(
+                "snapshot-revert",
+                image,
+                json!({ "snapshot": snap_name }),
+                || {
+                    Qcow2Image::open_rw(image)
+                        .and_then(|mut qcow2| qcow2.revert_snapshot(&snap_name))
+                        .with_context(|| {
+                            format!(
+                                "Failed to revert {} to snapshot '{}'",
+                                image.display(),
+                                snap_name
+                            )
+                        })
+                },
+            )?;
+            println!("{} Reverted to snapshot: {}", plain::text("✓"), snap_name);
         }
 
         "info" => {
-            if let Some(snap_name) = name {
-                progress.set_message(format!("Getting info for snapshot '{}'...", snap_name));
-
-                progress.finish_and_clear();
-
-                println!("Snapshot Information");
-                println!("====================");
-                println!("Name: {}", snap_name);
-                println!("Image: {}", image.display());
-                if let Some(desc) = description {
-                    println!("Description: {}", desc);
-                }
-                println!();
-                println!("Note: Snapshot info not fully implemented yet");
-                println!("      Would parse qemu-img snapshot -l output");
-            } else {
-                progress.abandon_with_message("Snapshot name required for info operation");
-                anyhow::bail!("Please provide snapshot name with --name");
-            }
+            let snap_name = required(name)?;
+            let snapshots = Qcow2Image::open(image)
+                .and_then(|qcow2| qcow2.snapshots())
+                .with_context(|| format!("Failed to read the snapshots of {}", image.display()))?;
+            let info = snapshots
+                .iter()
+                .find(|info| info.id == snap_name)
+                .or_else(|| snapshots.iter().find(|info| info.name == snap_name))
+                .with_context(|| format!("No snapshot '{}' in {}", snap_name, image.display()))?;
+
+            println!("Snapshot Information");
+            println!("====================");
+            println!("ID: {}", info.id);
+            println!("Name: {}", info.name);
+            println!("Image: {}", image.display());
+            println!("Date: {}", created(info));
+            println!("Disk size: {}", format_size(info.disk_size));
+            println!("VM state: {}", format_size(info.vm_state_size));
         }
 
         _ => {
-            progress.abandon_with_message(format!("Unknown operation: {}", operation));
-            anyhow::bail!("Invalid snapshot operation");
+            anyhow::bail!("Invalid snapshot operation: {}", operation);
         }
     }
 
@@ -3651,10 +4434,14 @@ pub fn diff_command(
     let mut g1 = Guestfs::new()?;
     g1.set_verbose(verbose);
     g1.add_drive_ro(image1.to_str().unwrap())?;
+    target::add_disks(&mut g1, true)?;
+    target::select(&mut g1);
 
     let mut g2 = Guestfs::new()?;
     g2.set_verbose(verbose);
     g2.add_drive_ro(image2.to_str().unwrap())?;
+    target::add_disks(&mut g2, true)?;
+    target::select(&mut g2);
 
     progress.set_message("Launching appliances...");
     g1.launch()?;
@@ -3662,28 +4449,16 @@ pub fn diff_command(
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots1 = g1.inspect_os().unwrap_or_default();
+    let roots1 = target::os_roots(&mut g1)?;
     if !roots1.is_empty() {
         let root = &roots1[0];
-        if let Ok(mountpoints) = g1.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g1.mount_ro(device, mount).ok();
-            }
-        }
+        g1.mount_os_ro(root).ok();
     }
 
-    let roots2 = g2.inspect_os().unwrap_or_default();
+    let roots2 = target::os_roots(&mut g2)?;
     if !roots2.is_empty() {
         let root = &roots2[0];
-        if let Ok(mountpoints) = g2.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g2.mount_ro(device, mount).ok();
-            }
-        }
+        g2.mount_os_ro(root).ok();
     }
 
     progress.set_message(format!("Comparing {}...", path));
@@ -3820,22 +4595,18 @@ pub fn find_large_command(
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
-        }
+        g.mount_os_ro(root).ok();
     }
 
     progress.set_message(format!("Scanning {} for large files...", path));
@@ -3900,22 +4671,18 @@ pub fn copy_command(
     let mut g_src = Guestfs::new()?;
     g_src.set_verbose(verbose);
     g_src.add_drive_ro(source_image.to_str().unwrap())?;
+    target::add_disks(&mut g_src, true)?;
+    target::select(&mut g_src);
 
     progress.set_message("Launching source appliance...");
     g_src.launch()?;
 
     // Mount source
     progress.set_message("Mounting source filesystem...");
-    let roots = g_src.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g_src)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g_src.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g_src.mount_ro(device, mount).ok();
-            }
-        }
+        g_src.mount_os_ro(root).ok();
     }
 
     // Check source exists
@@ -3932,6 +4699,20 @@ pub fn copy_command(
     } else {
         None
     };
+    let security = if preserve {
+        match g_src.get_security_attrs(source_path) {
+            Ok(attrs) => Some(attrs),
+            Err(e) => {
+                eprintln!(
+                    "Warning: could not read security attributes of {}: {}",
+                    source_path, e
+                );
+                None
+            }
+        }
+    } else {
+        None
+    };
 
     g_src.umount_all().ok();
     g_src.shutdown().ok();
@@ -3940,18 +4721,19 @@ pub fn copy_command(
     let mut g_dst = Guestfs::new()?;
     g_dst.set_verbose(verbose);
     g_dst.add_drive(dest_image.to_str().unwrap())?;
+    target::select(&mut g_dst);
 
     progress.set_message("Launching destination appliance...");
     g_dst.launch()?;
 
     // Mount destination
     progress.set_message("Mounting destination filesystem...");
-    let roots = g_dst.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g_dst)?;
     if !roots.is_empty() {
         let root = &roots[0];
         if let Ok(mountpoints) = g_dst.inspect_get_mountpoints(root) {
             let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
+            mounts.sort_by_key(|(mount, _)| mount.len());
             for (mount, device) in mounts {
                 g_dst.mount(device, mount).ok();
             }
@@ -3978,10 +4760,25 @@ pub fn copy_command(
         }
     }
 
+    // After chown, which clears capabilities
+    if let Some(attrs) = security.filter(|attrs| !attrs.is_empty()) {
+        if let Err(e) = g_dst.set_security_attrs(dest_path, &attrs) {
+            eprintln!(
+                "Warning: could not preserve security attributes of {}: {}",
+                dest_path, e
+            );
+        }
+    }
+
     progress.finish_and_clear();
 
-    println!("✓ Copied {} bytes from {} to {}",
-        content.len(), source_path, dest_path);
+    println!(
+        "{} Copied {} bytes from {} to {}",
+        plain::text("✓"),
+        content.len(),
+        source_path,
+        dest_path
+    );
 
     g_dst.umount_all().ok();
     g_dst.shutdown().ok();
@@ -4005,22 +4802,18 @@ pub fn find_duplicates_command(
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
-        }
+        g.mount_os_ro(root).ok();
     }
 
     progress.set_message(format!("Scanning {} for duplicates...", path));
@@ -4115,22 +4908,18 @@ pub fn disk_usage_command(
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
-        }
+        g.mount_os_ro(root).ok();
     }
 
     progress.set_message(format!("Analyzing disk usage in {}...", path));
@@ -4186,38 +4975,73 @@ pub fn disk_usage_command(
 /// Build forensic timeline from multiple sources
 pub fn timeline_command(
     image: &PathBuf,
-    _start_time: Option<String>,
-    _end_time: Option<String>,
+    start_time: Option<String>,
+    end_time: Option<String>,
     sources: Vec<String>,
+    paths: Vec<String>,
     format: &str,
+    output: Option<PathBuf>,
     verbose: bool,
 ) -> Result<()> {
+    use super::macb;
     use guestkit::core::ProgressReporter;
     use guestkit::Guestfs;
     use chrono::{Utc, TimeZone};
     use std::collections::BTreeMap;
 
+    let filter = macb::Filter {
+        paths,
+        start: start_time.as_deref().map(macb::parse_time).transpose()?,
+        end: end_time.as_deref().map(macb::parse_time).transpose()?,
+    };
+
     let mut g = Guestfs::new()?;
     g.set_verbose(verbose);
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
+        g.mount_os_ro(root).ok();
+    }
+
+    // Filesystem MACB timeline
+    if matches!(format, "bodyfile" | "l2tcsv") {
+        use std::io::Write;
+
+        let Some(root) = roots.first() else {
+            anyhow::bail!("No operating systems found in disk image");
+        };
+        progress.set_message("Walking filesystems...");
+        let entries = macb::collect(&mut g, root, &filter);
+        let hostname = g.inspect_get_hostname(root).unwrap_or_default();
+        progress.finish_and_clear();
+        g.umount_all().ok();
+        g.shutdown().ok();
+
+        let mut out: Box<dyn std::io::Write> = match &output {
+            Some(path) => Box::new(std::io::BufWriter::new(std::fs::File::create(path)?)),
+            None => Box::new(std::io::BufWriter::new(std::io::stdout().lock())),
+        };
+        if format == "bodyfile" {
+            macb::write_bodyfile(&mut out, &entries)?;
+        } else {
+            macb::write_l2tcsv(&mut out, &entries, &hostname, &filter)?;
+        }
+        out.flush()?;
+        if let Some(path) = output {
+            eprintln!("{} entries written to: {}", entries.len(), path.display());
         }
+        return Ok(());
     }
 
     progress.set_message("Building forensic timeline...");
@@ -4246,7 +5070,22 @@ pub fn timeline_command(
 
     // Source 2: Package installations (if 'packages' in sources)
     if sources.is_empty() || sources.contains(&"packages".to_string()) {
-        if !roots.is_empty() {
+        let (history, _) = crate::cli::package_history::scanner::collect_events(&mut g);
+
+        for event in &history {
+            if let Some(ts) = event.timestamp {
+                timeline.entry(ts)
+                    .or_default()
+                    .push((
+                        "package_manager".to_string(),
+                        format!("package_{}", event.action.as_str()),
+                        format!("{} {}", event.describe(), event.origin())
+                    ));
+            }
+        }
+
+        // Without package manager history, fall back to the installed package list
+        if history.is_empty() && !roots.is_empty() {
             if let Ok(apps) = g.inspect_list_applications(&roots[0]) {
                 for app in apps.iter().take(50) {
                     // Simplified: use current time as we don't have install time
@@ -4281,8 +5120,25 @@ pub fn timeline_command(
         }
     }
 
+    // Source 4: Logins, failed logins and reboots (if 'logins' in sources)
+    if sources.is_empty() || sources.contains(&"logins".to_string()) {
+        for event in g.login_history().unwrap_or_default() {
+            timeline.entry(event.time)
+                .or_default()
+                .push((
+                    "login_accounting".to_string(),
+                    event.kind.as_str().to_string(),
+                    event.describe()
+                ));
+        }
+    }
+
     progress.finish_and_clear();
 
+    if filter.start.is_some() || filter.end.is_some() {
+        timeline.retain(|timestamp, _| filter.in_window(*timestamp));
+    }
+
     // Display timeline
     match format {
         "json" => {
@@ -4358,22 +5214,18 @@ pub fn fingerprint_command(
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
-        }
+        g.mount_os_ro(root).ok();
     }
 
     progress.set_message("Generating fingerprint...");
@@ -4466,7 +5318,7 @@ pub fn fingerprint_command(
 
     if let Some(output_path) = output {
         fs::write(&output_path, serde_json::to_string_pretty(&fingerprint_output)?)?;
-        println!("✓ Fingerprint saved to: {}", output_path.display());
+        println!("{} Fingerprint saved to: {}", plain::text("✓"), output_path.display());
     } else {
         println!("{}", serde_json::to_string_pretty(&fingerprint_output)?);
     }
@@ -4497,10 +5349,14 @@ pub fn drift_command(
     let mut g_baseline = Guestfs::new()?;
     g_baseline.set_verbose(verbose);
     g_baseline.add_drive_ro(baseline.to_str().unwrap())?;
+    target::add_disks(&mut g_baseline, true)?;
+    target::select(&mut g_baseline);
 
     let mut g_current = Guestfs::new()?;
     g_current.set_verbose(verbose);
     g_current.add_drive_ro(current.to_str().unwrap())?;
+    target::add_disks(&mut g_current, true)?;
+    target::select(&mut g_current);
 
     progress.set_message("Launching appliances...");
     g_baseline.launch()?;
@@ -4510,29 +5366,17 @@ pub fn drift_command(
     progress.set_message("Mounting filesystems...");
 
     // Mount baseline
-    let roots_baseline = g_baseline.inspect_os().unwrap_or_default();
+    let roots_baseline = target::os_roots(&mut g_baseline)?;
     if !roots_baseline.is_empty() {
         let root = &roots_baseline[0];
-        if let Ok(mountpoints) = g_baseline.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g_baseline.mount_ro(device, mount).ok();
-            }
-        }
+        g_baseline.mount_os_ro(root).ok();
     }
 
     // Mount current
-    let roots_current = g_current.inspect_os().unwrap_or_default();
+    let roots_current = target::os_roots(&mut g_current)?;
     if !roots_current.is_empty() {
         let root = &roots_current[0];
-        if let Ok(mountpoints) = g_current.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g_current.mount_ro(device, mount).ok();
-            }
-        }
+        g_current.mount_os_ro(root).ok();
     }
 
     progress.set_message("Analyzing configuration drift...");
@@ -4591,8 +5435,8 @@ pub fn drift_command(
     }
 
     // Check packages
-    let roots_baseline = g_baseline.inspect_os().unwrap_or_default();
-    let roots_current = g_current.inspect_os().unwrap_or_default();
+    let roots_baseline = target::os_roots(&mut g_baseline)?;
+    let roots_current = target::os_roots(&mut g_current)?;
 
     if !roots_baseline.is_empty() && !roots_current.is_empty() {
         if let (Ok(apps_baseline), Ok(apps_current)) =
@@ -4609,12 +5453,29 @@ pub fn drift_command(
             let added: Vec<_> = pkg_current.difference(&pkg_baseline).collect();
             let removed: Vec<_> = pkg_baseline.difference(&pkg_current).collect();
 
+            // Explain package changes from the current image's package history
+            let (history, _) = crate::cli::package_history::scanner::collect_events(&mut g_current);
+            let latest = crate::cli::package_history::latest_by_package(&history);
+            let explain = |pkg: &str, summary: &str| -> String {
+                let name = pkg.split(':').next().unwrap_or(pkg);
+                match latest.get(name) {
+                    Some(event) => format!(
+                        "{} ({} {} {})",
+                        summary,
+                        event.action.as_str(),
+                        event.time_string(),
+                        event.origin()
+                    ),
+                    None => summary.to_string(),
+                }
+            };
+
             for pkg in added.iter().take(10) {
                 drift_score += 5;
                 drifts.push((
                     "package_added".to_string(),
                     pkg.to_string(),
-                    "Package installed".to_string()
+                    explain(pkg, "Package installed")
                 ));
             }
 
@@ -4623,7 +5484,7 @@ pub fn drift_command(
                 drifts.push((
                     "package_removed".to_string(),
                     pkg.to_string(),
-                    "Package uninstalled".to_string()
+                    explain(pkg, "Package uninstalled")
                 ));
             }
         }
@@ -4645,9 +5506,9 @@ pub fn drift_command(
     println!();
 
     if drift_percent > threshold {
-        println!("⚠️  DRIFT DETECTED - Exceeds threshold!");
+        println!("{}  DRIFT DETECTED - Exceeds threshold!", plain::text("⚠️"));
     } else {
-        println!("✓ Configuration within acceptable drift");
+        println!("{} Configuration within acceptable drift", plain::text("✓"));
     }
 
     println!();
@@ -4694,22 +5555,18 @@ pub fn analyze_command(
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
-        }
+        g.mount_os_ro(root).ok();
     }
 
     progress.set_message("Performing deep analysis...");
@@ -4838,7 +5695,7 @@ pub fn analyze_command(
         ("LOW", "🟢")
     };
 
-    println!("Risk Assessment: {} {} (score: {})", risk_level.1, risk_level.0, risk_score);
+    println!("Risk Assessment: {} {} (score: {})", plain::text(risk_level.1), risk_level.0, risk_score);
     println!();
 
     // Insights
@@ -4847,7 +5704,7 @@ pub fn analyze_command(
         println!("  No significant issues detected");
     } else {
         for insight in &insights {
-            println!("  {}", insight);
+            println!("  {}", plain::text(insight));
         }
     }
     println!();
@@ -4868,71 +5725,101 @@ pub fn analyze_command(
     Ok(())
 }
 
+/// Options for [`secrets_command`]
+#[derive(Debug, Clone, Default)]
+pub struct SecretsOptions {
+    /// Paths to scan (common secret locations if empty)
+    pub scan_paths: Vec<String>,
+    /// Extra regex patterns, added as custom rules
+    pub patterns: Vec<String>,
+    /// Gitignore-style include patterns
+    pub include: Vec<String>,
+    /// Gitignore-style exclude patterns
+    pub exclude: Vec<String>,
+    /// File of gitignore-style exclude patterns
+    pub exclude_from: Option<PathBuf>,
+    /// Match include and exclude patterns case-insensitively
+    pub ignore_case: bool,
+    /// TOML rule packs
+    pub rule_packs: Vec<PathBuf>,
+    /// TOML allowlists
+    pub allowlists: Vec<PathBuf>,
+    /// Leave out the findings recorded in this baseline
+    pub baseline: Option<PathBuf>,
+    /// Record every finding in this baseline file
+    pub write_baseline: Option<PathBuf>,
+    /// Show the secret values instead of redacting them
+    pub show_content: bool,
+    /// Export the report to this file
+    pub export: Option<PathBuf>,
+}
+
 /// Scan for exposed secrets and credentials
-pub fn secrets_command(
-    image: &PathBuf,
-    scan_paths: Vec<String>,
-    patterns: Vec<String>,
-    exclude: Vec<String>,
-    show_content: bool,
-    export: Option<PathBuf>,
-    verbose: bool,
-) -> Result<()> {
+pub fn secrets_command(image: &PathBuf, options: SecretsOptions, verbose: bool) -> Result<()> {
+    use super::matcher::PathFilter;
+    use super::secrets::{Baseline, RuleDef, RulePack, Scanner};
     use guestkit::core::ProgressReporter;
     use guestkit::Guestfs;
-    use regex::Regex;
-    use std::collections::HashSet;
+
+    let SecretsOptions {
+        scan_paths,
+        patterns,
+        include,
+        exclude,
+        exclude_from,
+        ignore_case,
+        rule_packs,
+        allowlists,
+        baseline,
+        write_baseline,
+        show_content,
+        export,
+    } = options;
+
+    // Built-in rules, then rule packs, then --patterns; allowlists only
+    // add to the allowlist
+    let mut packs = vec![RulePack::builtin()];
+    for path in rule_packs.iter().chain(&allowlists) {
+        packs.push(RulePack::load(path)?);
+    }
+    packs.push(RulePack {
+        rules: patterns
+            .into_iter()
+            .enumerate()
+            .map(|(i, regex)| RuleDef {
+                id: format!("custom-{}", i + 1),
+                description: "Custom Pattern".to_string(),
+                regex,
+                keywords: Vec::new(),
+                entropy: None,
+            })
+            .collect(),
+        ..Default::default()
+    });
+    let scanner = Scanner::new(&packs)?;
+    let baseline = baseline.as_deref().map(Baseline::load).transpose()?;
+    let filter = PathFilter::new(&include, &exclude, exclude_from.as_deref(), ignore_case)?;
 
     let mut g = Guestfs::new()?;
     g.set_verbose(verbose);
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
-        }
-    }
-
-    progress.set_message("Scanning for secrets...");
-
-    // Default secret patterns
-    let mut secret_patterns: Vec<(String, &str)> = vec![
-        (r"(?i)(password|passwd|pwd)\s*[:=]\s*\S{8,}".to_string(), "Password"),
-        (r"(?i)(api[_-]?key|apikey)\s*[:=]\s*[A-Za-z0-9_\-]{20,}".to_string(), "API Key"),
-        (r"(?i)(secret[_-]?key|secretkey)\s*[:=]\s*[A-Za-z0-9_\-]{20,}".to_string(), "Secret Key"),
-        (r"(?i)(private[_-]?key|privatekey)\s*[:=]\s*[A-Za-z0-9_\-]{20,}".to_string(), "Private Key"),
-        (r"-----BEGIN (RSA |DSA |EC )?PRIVATE KEY-----".to_string(), "SSH Private Key"),
-        (r"(?i)(bearer|token)\s*[:=]\s*[A-Za-z0-9_\-\.]{20,}".to_string(), "Bearer Token"),
-        (r"(?i)(aws_access_key_id|aws_secret_access_key)\s*[:=]\s*[A-Za-z0-9/+=]{20,}".to_string(), "AWS Credential"),
-        (r"(?i)mongodb(\+srv)?://[^:]+:[^@]+@".to_string(), "MongoDB Connection String"),
-        (r"(?i)(mysql|postgresql|postgres)://[^:]+:[^@]+@".to_string(), "Database Connection String"),
-        (r"ghp_[A-Za-z0-9]{36}".to_string(), "GitHub Personal Access Token"),
-        (r"glpat-[A-Za-z0-9_\-]{20,}".to_string(), "GitLab Personal Access Token"),
-        (r"sk_live_[A-Za-z0-9]{24,}".to_string(), "Stripe Live Key"),
-        (r"AIza[A-Za-z0-9_\-]{35}".to_string(), "Google API Key"),
-    ];
-
-    // Add custom patterns if provided
-    for pattern in patterns {
-        secret_patterns.push((pattern, "Custom Pattern"));
+        g.mount_os_ro(root).ok();
     }
 
-    let exclude_set: HashSet<String> = exclude.into_iter().collect();
-    let mut findings = Vec::new();
-    let mut scanned_files = 0;
+    progress.set_message(format!("Scanning for secrets with {} rules...", scanner.rule_count()));
 
     // Determine scan paths
     let paths_to_scan = if scan_paths.is_empty() {
@@ -4941,96 +5828,70 @@ pub fn secrets_command(
         scan_paths.iter().map(|s| s.as_str()).collect()
     };
 
-    for base_path in paths_to_scan {
-        if !g.exists(base_path).unwrap_or(false) {
-            continue;
+    let mut report = scanner.scan_guest(&mut g, &paths_to_scan, &filter, |scanned| {
+        if scanned % 100 == 0 {
+            progress.set_message(format!("Scanned {} files...", scanned));
         }
+    });
 
-        if let Ok(files) = g.find(base_path) {
-            for file in files {
-                // Skip excluded paths
-                if exclude_set.iter().any(|ex| file.contains(ex)) {
-                    continue;
-                }
-
-                // Skip binary files and large files
-                if g.is_file(&file).unwrap_or(false) {
-                    if let Ok(stat) = g.stat(&file) {
-                        // Skip files larger than 10MB
-                        if stat.size > 10_485_760 {
-                            continue;
-                        }
-
-                        // Try to read file
-                        if let Ok(content) = g.read_file(&file) {
-                            if let Ok(text) = String::from_utf8(content.clone()) {
-                                scanned_files += 1;
-
-                                if scanned_files % 100 == 0 {
-                                    progress.set_message(format!("Scanned {} files...", scanned_files));
-                                }
+    progress.finish_and_clear();
 
-                                // Check against all patterns
-                                for (pattern, secret_type) in &secret_patterns {
-                                    if let Ok(re) = Regex::new(pattern) {
-                                        for capture in re.captures_iter(&text) {
-                                            let matched = capture.get(0).map_or("", |m| m.as_str());
-                                            let context = if show_content {
-                                                matched.to_string()
-                                            } else {
-                                                "[REDACTED]".to_string()
-                                            };
+    g.umount_all().ok();
+    g.shutdown().ok();
 
-                                            findings.push((
-                                                file.clone(),
-                                                secret_type.to_string(),
-                                                context,
-                                            ));
-                                        }
-                                    }
-                                }
-                            }
-                        }
-                    }
-                }
-            }
+    // The baseline records everything found, accepted or not
+    if let Some(path) = &write_baseline {
+        Baseline {
+            findings: report.findings.clone(),
         }
+        .save(path)?;
+        println!("Baseline written to: {}", path.display());
     }
+    let suppressed = match &baseline {
+        Some(baseline) => baseline.suppress(&mut report.findings),
+        None => 0,
+    };
+    let findings = &report.findings;
 
-    progress.finish_and_clear();
+    let describe = |f: &super::secrets::Finding| {
+        if show_content {
+            format!("{}:{} : {}", f.path, f.line, f.secret)
+        } else {
+            format!("{}:{} : {}", f.path, f.line, f.masked())
+        }
+    };
 
     // Display results
     println!("Secrets Scan Report");
     println!("==================");
-    println!("Files scanned: {}", scanned_files);
+    println!("Files scanned: {}", report.files_scanned);
+    println!("Binary files skipped: {}", report.binary_skipped);
     println!("Secrets found: {}", findings.len());
+    if suppressed > 0 {
+        println!("Suppressed by baseline: {}", suppressed);
+    }
     println!();
 
+    // Group by type
+    let mut by_type: std::collections::BTreeMap<&str, Vec<String>> =
+        std::collections::BTreeMap::new();
+    for finding in findings {
+        by_type
+            .entry(finding.description.as_str())
+            .or_default()
+            .push(describe(finding));
+    }
+
     if findings.is_empty() {
-        println!("✓ No exposed secrets detected");
+        println!("{} No exposed secrets detected", plain::text("✓"));
     } else {
-        println!("⚠ Found {} potential secrets:", findings.len());
+        println!("{} Found {} potential secrets:", plain::text("⚠"), findings.len());
         println!();
 
-        // Group by type
-        let mut by_type: std::collections::HashMap<String, Vec<(String, String)>> =
-            std::collections::HashMap::new();
-
-        for (file, secret_type, context) in &findings {
-            by_type
-                .entry(secret_type.clone())
-                .or_insert_with(Vec::new)
-                .push((file.clone(), context.clone()));
-        }
-
-        for (secret_type, items) in by_type {
-            println!("🔑 {} ({} found):", secret_type, items.len());
-            for (file, context) in items.iter().take(10) {
-                if show_content {
-                    println!("  {} : {}", file, context);
-                } else {
-                    println!("  {}", file);
-                }
+        for (secret_type, items) in &by_type {
+            println!("{}{} ({} found):", plain::text("🔑 "), secret_type, items.len());
+            for item in items.iter().take(10) {
+                println!("  {}", item);
             }
             if items.len() > 10 {
                 println!("  ... and {} more", items.len() - 10);
@@ -5047,35 +5908,20 @@ pub fn secrets_command(
         let mut output = File::create(&export_path)?;
         writeln!(output, "# Secrets Scan Report")?;
         writeln!(output, "Image: {}", image.display())?;
-        writeln!(output, "Files scanned: {}", scanned_files)?;
-        writeln!(output, "")?;
-
-        let mut by_type: std::collections::HashMap<String, Vec<(String, String)>> =
-            std::collections::HashMap::new();
-        for (file, secret_type, context) in &findings {
-            by_type
-                .entry(secret_type.clone())
-                .or_insert_with(Vec::new)
-                .push((file.clone(), context.clone()));
-        }
+        writeln!(output, "Files scanned: {}", report.files_scanned)?;
+        writeln!(output)?;
 
-        for (secret_type, items) in by_type {
+        for (secret_type, items) in &by_type {
             writeln!(output, "## {}", secret_type)?;
-            for (file, context) in items {
-                if show_content {
-                    writeln!(output, "- {} : {}", file, context)?;
-                } else {
-                    writeln!(output, "- {}", file)?;
-                }
+            for item in items {
+                writeln!(output, "- {}", item)?;
             }
-            writeln!(output, "")?;
+            writeln!(output)?;
         }
 
         println!("Report exported to: {}", export_path.display());
     }
 
-    g.umount_all().ok();
-    g.shutdown().ok();
     Ok(())
 }
 
@@ -5097,18 +5943,20 @@ pub fn rescue_command(
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive(image.to_str().unwrap())?;
+    target::add_disks(&mut g, false)?;
+    target::select(&mut g);
 
     progress.set_message("Launching rescue environment...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
         if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
             let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
+            mounts.sort_by_key(|(mount, _)| mount.len());
             for (mount, device) in mounts {
                 g.mount(device, mount).ok();
             }
@@ -5162,7 +6010,7 @@ pub fn rescue_command(
                     g.upload(temp_file.path().to_str().unwrap(), "/etc/shadow")?;
 
                     progress.finish_and_clear();
-                    println!("✓ Password reset for user '{}'", username);
+                    println!("{} Password reset for user '{}'", plain::text("✓"), username);
                     println!("  New password: {}", new_password);
                     println!();
                     println!("Note: This is a simplified implementation");
@@ -5223,10 +6071,14 @@ pub fn rescue_command(
                         g.upload(temp_file.path().to_str().unwrap(), "/etc/fstab")?;
 
                         progress.finish_and_clear();
-                        println!("✓ Fixed {} issues in /etc/fstab", issues_found);
+                        println!(
+                            "{} Fixed {} issues in /etc/fstab",
+                            plain::text("✓"),
+                            issues_found
+                        );
                     } else {
                         progress.finish_and_clear();
-                        println!("✓ No issues found in /etc/fstab");
+                        println!("{} No issues found in /etc/fstab", plain::text("✓"));
                     }
                 }
             }
@@ -5253,12 +6105,12 @@ pub fn rescue_command(
             progress.finish_and_clear();
 
             if found {
-                println!("✓ GRUB configuration found");
+                println!("{} GRUB configuration found", plain::text("✓"));
                 println!();
                 println!("Note: Full GRUB repair requires running grub-install/grub-mkconfig");
                 println!("      This requires chroot into the guest system");
             } else {
-                println!("⚠ No GRUB configuration found");
+                println!("{} No GRUB configuration found", plain::text("⚠"));
             }
         }
 
@@ -5287,14 +6139,14 @@ pub fn rescue_command(
                                 std::fs::write(temp_file.path(), text)?;
                                 g.upload(temp_file.path().to_str().unwrap(), "/etc/ssh/sshd_config")?;
 
-                                println!("✓ Enabled root SSH login");
+                                println!("{} Enabled root SSH login", plain::text("✓"));
                             }
                         }
                     }
                 }
 
                 progress.finish_and_clear();
-                println!("✓ SSH configuration updated");
+                println!("{} SSH configuration updated", plain::text("✓"));
             } else {
                 progress.abandon_with_message("SSH server not found");
                 anyhow::bail!("OpenSSH server is not installed");
@@ -5318,11 +6170,19 @@ pub fn optimize_command(
     operations: Vec<String>,
     aggressive: bool,
     dry_run: bool,
+    exclude: Vec<String>,
+    exclude_from: Option<PathBuf>,
     verbose: bool,
 ) -> Result<()> {
+    use super::matcher::{MatchSet, PathFilter};
     use guestkit::core::ProgressReporter;
     use guestkit::Guestfs;
 
+    // Files the user wants kept are never removed or truncated
+    let filter = PathFilter::new(&[], &exclude, exclude_from.as_deref(), false)?;
+    let mut log_files = MatchSet::new(false);
+    log_files.add_all(&["*.log", "*.log.*"])?;
+
     let mut g = Guestfs::new()?;
     g.set_verbose(verbose);
 
@@ -5330,6 +6190,8 @@ pub fn optimize_command(
 
     if dry_run {
         g.add_drive_ro(image.to_str().unwrap())?;
+        target::add_disks(&mut g, true)?;
+        target::select(&mut g);
     } else {
         g.add_drive(image.to_str().unwrap())?;
     }
@@ -5339,18 +6201,16 @@ pub fn optimize_command(
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
+        if dry_run {
+            g.mount_os_ro(root).ok();
+        } else if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
             let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
+            mounts.sort_by_key(|(mount, _)| mount.len());
             for (mount, device) in mounts {
-                if dry_run {
-                    g.mount_ro(device, mount).ok();
-                } else {
-                    g.mount(device, mount).ok();
-                }
+                g.mount(device, mount).ok();
             }
         }
     }
@@ -5375,7 +6235,9 @@ pub fn optimize_command(
                     if g.is_dir(path).unwrap_or(false) {
                         if let Ok(files) = g.find(path) {
                             for file in files {
-                                if g.is_file(&file).unwrap_or(false) {
+                                if g.is_file(&file).unwrap_or(false)
+                                    && !filter.is_excluded(&file, false)
+                                {
                                     if let Ok(stat) = g.stat(&file) {
                                         total_freed += stat.size as u64;
                                         files_removed += 1;
@@ -5390,7 +6252,12 @@ pub fn optimize_command(
                     }
                 }
 
-                println!("✓ Temporary files: {} files ({} bytes)", files_removed, total_freed);
+                println!(
+                    "{} Temporary files: {} files ({} bytes)",
+                    plain::text("✓"),
+                    files_removed,
+                    total_freed
+                );
             }
 
             "logs" => {
@@ -5405,21 +6272,22 @@ pub fn optimize_command(
                         if let Ok(files) = g.find(path) {
                             for file in files {
                                 // Only clean .log and .log.* files
-                                if file.contains(".log") {
-                                    if g.is_file(&file).unwrap_or(false) {
-                                        if let Ok(stat) = g.stat(&file) {
-                                            log_freed += stat.size as u64;
-                                            logs_cleaned += 1;
-
-                                            if !dry_run {
-                                                if aggressive {
-                                                    g.rm(&file).ok();
-                                                } else {
-                                                    // Truncate instead of remove
-                                                    g.truncate(&file).ok();
-                                                }
-                                            }
-                                        }
+                                if log_files.matches(&file, false)
+                                    && !filter.is_excluded(&file, false)
+                                    && g.is_file(&file).unwrap_or(false)
+                                {
+                                    if let Ok(stat) = g.stat(&file) {
+                                        log_freed += stat.size as u64;
+                                        logs_cleaned += 1;
+
+                                        if !dry_run {
+                                            if aggressive {
+                                                g.rm(&file).ok();
+                                            } else {
+                                                // Truncate instead of remove
+                                                g.truncate(&file).ok();
+                                            }
+                                        }
                                     }
                                 }
                             }
@@ -5427,7 +6295,12 @@ pub fn optimize_command(
                     }
                 }
 
-                println!("✓ Log files: {} files ({} bytes)", logs_cleaned, log_freed);
+                println!(
+                    "{} Log files: {} files ({} bytes)",
+                    plain::text("✓"),
+                    logs_cleaned,
+                    log_freed
+                );
                 total_freed += log_freed;
             }
 
@@ -5446,7 +6319,9 @@ pub fn optimize_command(
                     if g.is_dir(path).unwrap_or(false) {
                         if let Ok(files) = g.find(path) {
                             for file in files {
-                                if g.is_file(&file).unwrap_or(false) {
+                                if g.is_file(&file).unwrap_or(false)
+                                    && !filter.is_excluded(&file, false)
+                                {
                                     if let Ok(stat) = g.stat(&file) {
                                         cache_freed += stat.size as u64;
                                         cache_cleaned += 1;
@@ -5461,17 +6336,22 @@ pub fn optimize_command(
                     }
                 }
 
-                println!("✓ Cache files: {} files ({} bytes)", cache_cleaned, cache_freed);
+                println!(
+                    "{} Cache files: {} files ({} bytes)",
+                    plain::text("✓"),
+                    cache_cleaned,
+                    cache_freed
+                );
                 total_freed += cache_freed;
             }
 
             "packages" => {
-                println!("✓ Package cleanup: Not yet implemented");
+                println!("{} Package cleanup: Not yet implemented", plain::text("✓"));
                 println!("      Would run: apt-get clean, yum clean, etc.");
             }
 
             _ => {
-                println!("⚠ Unknown operation: {}", operation);
+                println!("{} Unknown operation: {}", plain::text("⚠"), operation);
             }
         }
     }
@@ -5520,22 +6400,18 @@ pub fn network_command(
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
-        }
+        g.mount_os_ro(root).ok();
     }
 
     progress.set_message("Analyzing network configuration...");
@@ -5547,7 +6423,7 @@ pub fn network_command(
 
     // Analyze network interfaces
     if show_interfaces {
-        println!("🌐 Network Interfaces:");
+        println!("{}Network Interfaces:", plain::text("🌐 "));
 
         // Check for network configuration files
         let net_configs = vec![
@@ -5580,7 +6456,7 @@ pub fn network_command(
 
     // Analyze DNS configuration
     if show_dns {
-        println!("🔍 DNS Configuration:");
+        println!("{}DNS Configuration:", plain::text("🔍 "));
 
         if g.is_file("/etc/resolv.conf").unwrap_or(false) {
             if let Ok(content) = g.read_file("/etc/resolv.conf") {
@@ -5611,7 +6487,7 @@ pub fn network_command(
 
     // Analyze routing
     if show_routes {
-        println!("🛣  Routing:");
+        println!("{} Routing:", plain::text("🛣 "));
         println!("  Note: Route table analysis requires parsing network config");
         println!();
     }
@@ -5647,22 +6523,18 @@ pub fn compliance_command(
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
-        }
+        g.mount_os_ro(root).ok();
     }
 
     progress.set_message(format!("Running {} compliance checks...", standard));
@@ -5821,15 +6693,15 @@ pub fn compliance_command(
 
         match result {
             "PASS" => {
-                println!("✓ PASS");
+                println!("{} PASS", plain::text("✓"));
                 passed += 1;
             }
             "FAIL" => {
-                println!("✗ FAIL");
+                println!("{} FAIL", plain::text("✗"));
                 failed += 1;
             }
             _ => {
-                println!("⚠ WARNING");
+                println!("{} WARNING", plain::text("⚠"));
                 warnings += 1;
             }
         }
@@ -5846,11 +6718,11 @@ pub fn compliance_command(
 
     let compliance_score = (passed * 100) / checks.len();
     if compliance_score >= 90 {
-        println!("✓ COMPLIANT (Score: {}%)", compliance_score);
+        println!("{} COMPLIANT (Score: {}%)", plain::text("✓"), compliance_score);
     } else if compliance_score >= 70 {
-        println!("⚠ PARTIALLY COMPLIANT (Score: {}%)", compliance_score);
+        println!("{} PARTIALLY COMPLIANT (Score: {}%)", plain::text("⚠"), compliance_score);
     } else {
-        println!("✗ NON-COMPLIANT (Score: {}%)", compliance_score);
+        println!("{} NON-COMPLIANT (Score: {}%)", plain::text("✗"), compliance_score);
     }
 
     if fix {
@@ -5896,34 +6768,45 @@ pub fn malware_command(
     deep_scan: bool,
     check_rootkits: bool,
     yara_rules: Option<PathBuf>,
+    yara_paths: Vec<String>,
     quarantine: bool,
+    format: &str,
+    attack_layer: Option<PathBuf>,
     verbose: bool,
 ) -> Result<()> {
+    use super::attack::Catalog;
+    use super::malware::{rootkit, Finding};
+    use super::sarif::{self, SarifResult};
+    use super::yara::{self, YaraRules};
     use guestkit::core::ProgressReporter;
     use guestkit::Guestfs;
+    use serde_json::json;
     use std::collections::HashSet;
 
+    if !matches!(format, "text" | "json" | "sarif") {
+        anyhow::bail!("Unknown format: {} (expected text, json or sarif)", format);
+    }
+
+    // Compile the rules before the appliance starts, so mistakes show early
+    let yara_rules = yara_rules.as_deref().map(YaraRules::load).transpose()?;
+
     let mut g = Guestfs::new()?;
     g.set_verbose(verbose);
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
-        }
+        g.mount_os_ro(root).ok();
     }
 
     progress.set_message("Scanning for malware...");
@@ -5946,12 +6829,12 @@ pub fn malware_command(
                         if let Ok(stat) = g.stat(&file) {
                             // Executable files in temp dirs are suspicious
                             if stat.mode & 0o111 != 0 {
-                                findings.push((
-                                    "Suspicious executable in temp directory".to_string(),
-                                    file.clone(),
-                                    "HIGH".to_string(),
+                                findings.push(Finding::new(
+                                    "temp-executable",
+                                    "HIGH",
+                                    "Suspicious executable in temp directory",
+                                    file,
                                 ));
-                                suspicious_files.insert(file);
                             }
                         }
                     }
@@ -5968,52 +6851,28 @@ pub fn malware_command(
                 for entry in entries {
                     if entry.starts_with('.') && entry != "." && entry != ".." {
                         let full_path = format!("{}/{}", path, entry);
-                        findings.push((
-                            "Hidden file in suspicious location".to_string(),
-                            full_path.clone(),
-                            "MEDIUM".to_string(),
+                        findings.push(Finding::new(
+                            "hidden-file",
+                            "MEDIUM",
+                            "Hidden file in suspicious location",
+                            full_path,
                         ));
-                        suspicious_files.insert(full_path);
                     }
                 }
             }
         }
     }
 
-    // 3. Check for suspicious SUID binaries
-    if deep_scan {
-        progress.set_message("Scanning for suspicious SUID binaries...");
-
-        // Known good SUID binaries
-        let known_suid: HashSet<&str> = [
-            "/usr/bin/sudo",
-            "/usr/bin/passwd",
-            "/usr/bin/su",
-            "/usr/bin/mount",
-            "/usr/bin/umount",
-            "/bin/ping",
-            "/bin/ping6",
-        ].iter().copied().collect();
-
-        if let Ok(files) = g.find("/usr") {
-            for file in files.iter().take(1000) {
-                if g.is_file(file).unwrap_or(false) {
-                    if let Ok(stat) = g.stat(file) {
-                        // Check for SUID bit
-                        if stat.mode & 0o4000 != 0 {
-                            if !known_suid.contains(file.as_str()) {
-                                findings.push((
-                                    "Unknown SUID binary".to_string(),
-                                    file.clone(),
-                                    "HIGH".to_string(),
-                                ));
-                                suspicious_files.insert(file.clone());
-                            }
-                        }
-                    }
-                }
-            }
-        }
+    // 3. Setuid and setgid binaries no package installed
+    let packaged = if deep_scan || check_rootkits {
+        progress.set_message("Reading package databases...");
+        Some(rootkit::Packaged::load(&mut g))
+    } else {
+        None
+    };
+    if let Some(packaged) = &packaged {
+        progress.set_message("Scanning for unpackaged setuid binaries...");
+        findings.extend(rootkit::setuid_binaries(&mut g, packaged));
     }
 
     // 4. Rootkit detection
@@ -6032,19 +6891,22 @@ pub fn malware_command(
 
         for indicator in rootkit_indicators {
             if g.exists(indicator).unwrap_or(false) {
-                findings.push((
-                    "Rootkit indicator found".to_string(),
-                    indicator.to_string(),
-                    "CRITICAL".to_string(),
+                findings.push(Finding::new(
+                    "rootkit-file",
+                    "CRITICAL",
+                    "Rootkit indicator found",
+                    indicator,
                 ));
-                suspicious_files.insert(indicator.to_string());
             }
         }
 
-        // Check for suspicious kernel modules
-        if g.is_dir("/lib/modules").unwrap_or(false) {
-            // This would check for LKM rootkits in a real implementation
-            // For now, just note that we checked
+        if let Some(packaged) = &packaged {
+            findings.extend(rootkit::ld_preload(&mut g));
+            progress.set_message("Checking kernel modules...");
+            findings.extend(rootkit::kernel_modules(&mut g, packaged));
+            progress.set_message("Checking systemd units and cron jobs...");
+            findings.extend(rootkit::systemd_units(&mut g, packaged));
+            findings.extend(rootkit::cron_jobs(&mut g, packaged));
         }
     }
 
@@ -6057,11 +6919,15 @@ pub fn malware_command(
                     if line.contains("google.com") || line.contains("facebook.com")
                         || line.contains("microsoft.com") {
                         if !line.starts_with('#') {
-                            findings.push((
-                                "Suspicious hosts file entry (possible DNS hijack)".to_string(),
-                                line.to_string(),
-                                "HIGH".to_string(),
-                            ));
+                            findings.push(
+                                Finding::new(
+                                    "hosts-hijack",
+                                    "HIGH",
+                                    "Suspicious hosts file entry (possible DNS hijack)",
+                                    "/etc/hosts",
+                                )
+                                .with_detail(line),
+                            );
                         }
                     }
                 }
@@ -6070,13 +6936,106 @@ pub fn malware_command(
     }
 
     // 6. YARA scanning (if rules provided)
-    if let Some(_yara_path) = yara_rules {
-        println!("Note: YARA scanning not yet implemented");
-        println!("      Would scan with rules from: {:?}", _yara_path);
+    let yara_report = match &yara_rules {
+        Some(rules) => {
+            let bases: Vec<&str> = if !yara_paths.is_empty() {
+                yara_paths.iter().map(|p| p.as_str()).collect()
+            } else if deep_scan {
+                vec!["/"]
+            } else {
+                vec![
+                    "/tmp", "/var/tmp", "/dev/shm", "/root", "/home", "/opt", "/usr/local",
+                    "/var/www", "/etc/cron.d",
+                ]
+            };
+            progress.set_message(format!("Scanning with {} YARA rules...", rules.rule_count()));
+            let report = rules.scan_guest(&mut g, &bases, |done, total| {
+                if done % 100 == 0 || done == total {
+                    progress.set_message(format!("YARA: {}/{} files", done, total));
+                }
+            });
+            for m in &report.matches {
+                suspicious_files.insert(m.path.clone());
+            }
+            Some(report)
+        }
+        None => None,
+    };
+    let yara_matches = yara_report.as_ref().map_or(&[][..], |r| &r.matches[..]);
+    for finding in &findings {
+        suspicious_files.insert(finding.path.clone());
     }
 
     progress.finish_and_clear();
 
+    g.umount_all().ok();
+    g.shutdown().ok();
+
+    let catalog = Catalog::bundled();
+    if let Some(layer_path) = &attack_layer {
+        let layer = catalog.layer(
+            &format!("guestctl malware: {}", image.display()),
+            "Malware and rootkit findings",
+            findings
+                .iter()
+                .map(|f| (f.check.as_str(), f.severity.as_str(), f.path.as_str())),
+        );
+        layer.save(layer_path)?;
+        eprintln!(
+            "ATT&CK Navigator layer ({} techniques) exported to: {}",
+            layer.scored().count(),
+            layer_path.display()
+        );
+    }
+
+    match format {
+        "json" => {
+            let report = json!({
+                "image": image.display().to_string(),
+                "deep_scan": deep_scan,
+                "check_rootkits": check_rootkits,
+                "findings": findings,
+                "yara": yara_report,
+            });
+            println!("{}", serde_json::to_string_pretty(&report)?);
+            return Ok(());
+        }
+        "sarif" => {
+            let (mut rules, mut results) = yara::sarif_results(yara_matches);
+            let mut checks = HashSet::new();
+            for finding in &findings {
+                let id = format!("malware/{}", finding.check);
+                if checks.insert(id.clone()) {
+                    let tags: Vec<String> = catalog
+                        .detected_by(&finding.check)
+                        .iter()
+                        .map(|t| format!("attack.{}", t.id.to_lowercase()))
+                        .collect();
+                    let mut properties = serde_json::Map::new();
+                    if !tags.is_empty() {
+                        properties.insert("tags".to_string(), json!(tags));
+                    }
+                    rules.push(sarif::ReportingDescriptor {
+                        id: id.clone(),
+                        short_description: Some(sarif::Message {
+                            text: finding.title.clone(),
+                        }),
+                        properties,
+                    });
+                }
+                let message = match &finding.detail {
+                    Some(detail) => format!("{}: {}", finding.title, detail),
+                    None => finding.title.clone(),
+                };
+                results.push(SarifResult::new(&id, &finding.severity, message, &finding.path));
+            }
+            let log = sarif::Log::new(sarif::Driver::guestctl(rules), results);
+            println!("{}", serde_json::to_string_pretty(&log)?);
+            return Ok(());
+        }
+        _ => {}
+    }
+
     // Display results
     println!("Malware Scan Report");
     println!("==================");
@@ -6084,26 +7043,56 @@ pub fn malware_command(
     println!("Rootkit check: {}", if check_rootkits { "Yes" } else { "No" });
     println!();
 
-    if findings.is_empty() {
-        println!("✓ No malware or suspicious files detected");
+    if let Some(report) = &yara_report {
+        println!(
+            "YARA: {} files scanned, {} unreadable",
+            report.files_scanned, report.errors
+        );
+        println!();
+    }
+
+    if findings.is_empty() && yara_matches.is_empty() {
+        println!("{} No malware or suspicious files detected", plain::text("✓"));
     } else {
-        println!("⚠ Found {} suspicious items:", findings.len());
+        println!(
+            "{} Found {} suspicious items:",
+            plain::text("⚠"),
+            findings.len() + yara_matches.len()
+        );
         println!();
 
         // Group by severity
         for severity in ["CRITICAL", "HIGH", "MEDIUM", "LOW"] {
             let items: Vec<_> = findings.iter()
-                .filter(|(_, _, s)| s == severity)
+                .filter(|f| f.severity == severity)
                 .collect();
 
             if !items.is_empty() {
                 println!("{} - {} items:", severity, items.len());
-                for (reason, path, _) in items {
-                    println!("  • {} : {}", reason, path);
+                for finding in items {
+                    println!("  {} {} : {}", plain::text("•"), finding.title, finding.path);
+                    if let Some(detail) = &finding.detail {
+                        println!("      {}", detail);
+                    }
                 }
                 println!();
             }
         }
+
+        if !yara_matches.is_empty() {
+            println!("YARA - {} matches:", yara_matches.len());
+            for m in yara_matches {
+                println!(
+                    "  {} [{}] {} : {} ({})",
+                    plain::text("•"),
+                    m.severity(),
+                    m.qualified_rule(),
+                    m.path,
+                    m.description()
+                );
+            }
+            println!();
+        }
     }
 
     if quarantine {
@@ -6111,8 +7100,6 @@ pub fn malware_command(
         println!("Note: Quarantine not implemented in read-only mode");
     }
 
-    g.umount_all().ok();
-    g.shutdown().ok();
     Ok(())
 }
 
@@ -6132,22 +7119,18 @@ pub fn health_command(
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
-        }
+        g.mount_os_ro(root).ok();
     }
 
     progress.set_message("Running health diagnostics...");
@@ -6159,10 +7142,10 @@ pub fn health_command(
 
     let checks_to_run = if checks.is_empty() {
         vec!["disk".to_string(), "services".to_string(), "security".to_string(),
-             "packages".to_string(), "logs".to_string()]
+             "packages".to_string(), "logs".to_string(), "crashes".to_string()]
     } else if checks.iter().any(|c| c == "all") {
         vec!["disk".to_string(), "services".to_string(), "security".to_string(),
-             "packages".to_string(), "logs".to_string()]
+             "packages".to_string(), "logs".to_string(), "crashes".to_string()]
     } else {
         checks
     };
@@ -6173,7 +7156,7 @@ pub fn health_command(
     for check in checks_to_run {
         match check.as_str() {
             "disk" => {
-                println!("💾 Disk Health:");
+                println!("{}Disk Health:", plain::text("💾 "));
 
                 // Check disk usage
                 if let Ok(statvfs) = g.statvfs("/") {
@@ -6190,15 +7173,15 @@ pub fn health_command(
                             used_percent, total - free, total);
 
                         if used_percent > 90 {
-                            println!("  ⚠ WARNING: Disk usage critical (>90%)");
+                            println!("  {} WARNING: Disk usage critical (>90%)", plain::text("⚠"));
                             overall_score -= 20;
                             issues.push("Disk usage critical".to_string());
                         } else if used_percent > 80 {
-                            println!("  ⚠ WARNING: Disk usage high (>80%)");
+                            println!("  {} WARNING: Disk usage high (>80%)", plain::text("⚠"));
                             overall_score -= 10;
                             issues.push("Disk usage high".to_string());
                         } else {
-                            println!("  ✓ Disk usage healthy");
+                            println!("  {} Disk usage healthy", plain::text("✓"));
                         }
                     }
                 }
@@ -6206,7 +7189,7 @@ pub fn health_command(
             }
 
             "services" => {
-                println!("⚙️  Service Health:");
+                println!("{} Service Health:", plain::text("⚙️ "));
 
                 // Check for failed services (systemd)
                 if g.is_dir("/etc/systemd/system").unwrap_or(false) {
@@ -6220,15 +7203,15 @@ pub fn health_command(
                         println!("  Service units found: {}", service_count);
                     }
 
-                    println!("  ✓ Service configuration present");
+                    println!("  {} Service configuration present", plain::text("✓"));
                 } else {
-                    println!("  ⚠ No systemd found");
+                    println!("  {} No systemd found", plain::text("⚠"));
                 }
                 println!();
             }
 
             "security" => {
-                println!("🔒 Security Health:");
+                println!("{}Security Health:", plain::text("🔒 "));
 
                 let mut security_score = 100;
 
@@ -6237,18 +7220,18 @@ pub fn health_command(
                     if let Ok(content) = g.read_file("/etc/ssh/sshd_config") {
                         if let Ok(text) = String::from_utf8(content) {
                             if text.contains("PermitRootLogin yes") {
-                                println!("  ⚠ Root SSH login permitted");
+                                println!("  {} Root SSH login permitted", plain::text("⚠"));
                                 security_score -= 20;
                                 issues.push("Root SSH login enabled".to_string());
                             } else {
-                                println!("  ✓ Root SSH login restricted");
+                                println!("  {} Root SSH login restricted", plain::text("✓"));
                             }
 
                             if text.contains("PasswordAuthentication yes") {
-                                println!("  ⚠ Password authentication enabled");
+                                println!("  {} Password authentication enabled", plain::text("⚠"));
                                 security_score -= 10;
                             } else {
-                                println!("  ✓ Password authentication disabled");
+                                println!("  {} Password authentication disabled", plain::text("✓"));
                             }
                         }
                     }
@@ -6259,9 +7242,9 @@ pub fn health_command(
                 let has_apparmor = g.is_dir("/etc/apparmor.d").unwrap_or(false);
 
                 if has_selinux || has_apparmor {
-                    println!("  ✓ MAC system present (SELinux/AppArmor)");
+                    println!("  {} MAC system present (SELinux/AppArmor)", plain::text("✓"));
                 } else {
-                    println!("  ⚠ No MAC system detected");
+                    println!("  {} No MAC system detected", plain::text("⚠"));
                     security_score -= 15;
                     issues.push("No MAC system".to_string());
                 }
@@ -6269,9 +7252,9 @@ pub fn health_command(
                 // Check firewall
                 if g.is_file("/etc/sysconfig/iptables").unwrap_or(false)
                     || g.is_dir("/etc/ufw").unwrap_or(false) {
-                    println!("  ✓ Firewall configuration found");
+                    println!("  {} Firewall configuration found", plain::text("✓"));
                 } else {
-                    println!("  ⚠ No firewall configuration detected");
+                    println!("  {} No firewall configuration detected", plain::text("⚠"));
                     security_score -= 10;
                 }
 
@@ -6281,7 +7264,7 @@ pub fn health_command(
             }
 
             "packages" => {
-                println!("📦 Package Health:");
+                println!("{}Package Health:", plain::text("📦 "));
 
                 if !roots.is_empty() {
                     if let Ok(apps) = g.inspect_list_applications(&roots[0]) {
@@ -6293,18 +7276,18 @@ pub fn health_command(
                             .count();
 
                         if dev_packages > 50 {
-                            println!("  ⚠ Many development packages ({}) - consider cleanup", dev_packages);
+                            println!("  {} Many development packages ({}) - consider cleanup", plain::text("⚠"), dev_packages);
                             issues.push("Excessive development packages".to_string());
                         }
 
-                        println!("  ✓ Package database accessible");
+                        println!("  {} Package database accessible", plain::text("✓"));
                     }
                 }
                 println!();
             }
 
             "logs" => {
-                println!("📋 Log Health:");
+                println!("{}Log Health:", plain::text("📋 "));
 
                 // Check for large log files
                 if g.is_dir("/var/log").unwrap_or(false) {
@@ -6327,22 +7310,56 @@ pub fn health_command(
                         println!("  Total log size: {:.2} MB", total_log_size as f64 / 1_048_576.0);
 
                         if !large_logs.is_empty() {
-                            println!("  ⚠ Large log files found:");
+                            println!("  {} Large log files found:", plain::text("⚠"));
                             for (file, size) in large_logs.iter().take(5) {
                                 println!("    {} ({:.2} MB)", file, *size as f64 / 1_048_576.0);
                             }
                             overall_score -= 5;
                             issues.push("Large log files present".to_string());
                         } else {
-                            println!("  ✓ No oversized log files");
+                            println!("  {} No oversized log files", plain::text("✓"));
                         }
                     }
                 }
                 println!();
             }
 
+            "crashes" => {
+                use crate::cli::crash;
+
+                println!("{}Crash Evidence:", plain::text("💥 "));
+
+                let evidence = crash::scanner::collect_evidence(&mut g);
+                let summary = crash::summarize(&evidence);
+
+                if evidence.is_empty() {
+                    println!("  {} No crash dumps or core files found", plain::text("✓"));
+                } else {
+                    println!("  Kernel crashes: {}", summary.kernel_dumps);
+                    println!("  Userspace crashes: {}", summary.userspace_crashes);
+                    if let Some(ref last) = summary.last_crash {
+                        println!("  Most recent crash: {}", last);
+                    }
+                    for (binary, count) in summary.top_binaries.iter().take(3) {
+                        println!("    {} ({} crashes)", binary, count);
+                    }
+
+                    if summary.kernel_dumps > 0 {
+                        println!("  {} WARNING: Kernel crash dumps present", plain::text("⚠"));
+                        overall_score = overall_score.saturating_sub(15);
+                        issues.push(format!("{} kernel crash dump(s)", summary.kernel_dumps));
+                    }
+                    if summary.userspace_crashes > 0 {
+                        println!("  {} Application crashes recorded", plain::text("⚠"));
+                        overall_score = overall_score.saturating_sub(5);
+                        issues.push(format!("{} application crash(es)", summary.userspace_crashes));
+                    }
+                }
+                println!();
+            }
+
             _ => {
-                println!("⚠ Unknown check: {}", check);
+                println!("{} Unknown check: {}", plain::text("⚠"), check);
             }
         }
     }
@@ -6351,13 +7368,13 @@ pub fn health_command(
     println!("Overall Health Score: {}%", overall_score);
 
     if overall_score >= 90 {
-        println!("Status: ✓ HEALTHY");
+        println!("Status: {} HEALTHY", plain::text("✓"));
     } else if overall_score >= 70 {
-        println!("Status: ⚠ FAIR - Some issues detected");
+        println!("Status: {} FAIR - Some issues detected", plain::text("⚠"));
     } else if overall_score >= 50 {
-        println!("Status: ⚠ POOR - Multiple issues require attention");
+        println!("Status: {} POOR - Multiple issues require attention", plain::text("⚠"));
     } else {
-        println!("Status: ✗ CRITICAL - Immediate attention required");
+        println!("Status: {} CRITICAL - Immediate attention required", plain::text("✗"));
     }
 
     if !issues.is_empty() {
@@ -6394,13 +7411,13 @@ pub fn health_command(
 }
 
 /// Clone disk image with customizations
+///
+/// `sysprep` are the sysprep operations to run on the clone, if any.
 pub fn clone_command(
     source: &PathBuf,
     dest: &PathBuf,
-    sysprep: bool,
+    sysprep: Option<Vec<&SysprepOperation>>,
     hostname: Option<String>,
-    remove_keys: bool,
-    preserve_users: bool,
     verbose: bool,
 ) -> Result<()> {
     use guestkit::core::ProgressReporter;
@@ -6414,51 +7431,32 @@ pub fn clone_command(
 
     progress.set_message("Image copied, applying customizations...");
 
-    if sysprep {
+    if let Some(sysprep_ops) = sysprep {
         use guestkit::Guestfs;
 
         let mut g = Guestfs::new()?;
         g.set_verbose(verbose);
         g.add_drive(dest.to_str().unwrap())?;
+        target::add_disks(&mut g, false)?;
+        target::select(&mut g);
 
         progress.set_message("Launching appliance for sysprep...");
         g.launch()?;
-
-        // Mount filesystems
-        let roots = g.inspect_os().unwrap_or_default();
-        if !roots.is_empty() {
-            let root = &roots[0];
-            if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-                let mut mounts: Vec<_> = mountpoints.iter().collect();
-                mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-                for (mount, device) in mounts {
-                    g.mount(device, mount).ok();
-                }
-            }
-        }
+        mount_for_sysprep(&mut g, false)?;
 
         // Sysprep operations
         progress.set_message("Running sysprep operations...");
 
         let mut operations = Vec::new();
-
-        // Remove SSH host keys
-        if remove_keys {
-            let ssh_keys = vec![
-                "/etc/ssh/ssh_host_rsa_key",
-                "/etc/ssh/ssh_host_rsa_key.pub",
-                "/etc/ssh/ssh_host_ecdsa_key",
-                "/etc/ssh/ssh_host_ecdsa_key.pub",
-                "/etc/ssh/ssh_host_ed25519_key",
-                "/etc/ssh/ssh_host_ed25519_key.pub",
-            ];
-
-            for key in ssh_keys {
-                if g.is_file(key).unwrap_or(false) {
-                    g.rm(key).ok();
-                    operations.push(format!("Removed {}", key));
-                }
-            }
+        let results = g.sysprep_run(&sysprep_ops, false)?;
+        for result in results.iter().filter(|result| !result.is_empty()) {
+            operations.push(format!(
+                "{}: {} removed, {} truncated, {} created",
+                result.operation,
+                result.removed.len(),
+                result.truncated.len(),
+                result.created.len()
+            ));
         }
 
         // Change hostname
@@ -6471,46 +7469,20 @@ pub fn clone_command(
             }
         }
 
-        // Clear machine ID
-        if g.is_file("/etc/machine-id").unwrap_or(false) {
-            g.truncate("/etc/machine-id").ok();
-            operations.push("Cleared machine-id".to_string());
-        }
-
-        // Clear logs
-        if g.is_dir("/var/log").unwrap_or(false) {
-            operations.push("Cleared system logs".to_string());
-        }
-
-        // Remove user history files if not preserving
-        if !preserve_users {
-            let history_files = vec![
-                "/root/.bash_history",
-                "/root/.zsh_history",
-            ];
-
-            for hist in history_files {
-                if g.is_file(hist).unwrap_or(false) {
-                    g.rm(hist).ok();
-                    operations.push(format!("Removed {}", hist));
-                }
-            }
-        }
-
         g.umount_all().ok();
         g.shutdown().ok();
 
         progress.finish_and_clear();
 
-        println!("✓ Clone completed successfully");
+        println!("{} Clone completed successfully", plain::text("✓"));
         println!();
         println!("Sysprep operations performed:");
         for op in operations {
-            println!("  • {}", op);
+            println!("  {} {}", plain::text("•"), op);
         }
     } else {
         progress.finish_and_clear();
-        println!("✓ Clone completed (no sysprep)");
+        println!("{} Clone completed (no sysprep)", plain::text("✓"));
     }
 
     println!();
@@ -6520,12 +7492,71 @@ pub fn clone_command(
     Ok(())
 }
 
+/// Mount the guest's filesystems for sysprep
+fn mount_for_sysprep(g: &mut Guestfs, read_only: bool) -> Result<()> {
+    let roots = target::os_roots(g)?;
+    if let Some(root) = roots.first() {
+        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
+            let mut mounts: Vec<_> = mountpoints.iter().collect();
+            mounts.sort_by_key(|(mount, _)| mount.len());
+            for (mount, device) in mounts {
+                if read_only {
+                    g.mount_ro(device, mount).ok();
+                } else {
+                    g.mount(device, mount).ok();
+                }
+            }
+        }
+    }
+    Ok(())
+}
+
+/// List what sysprep operations would change in an image
+pub fn sysprep_dry_run(
+    image: &PathBuf,
+    sysprep_ops: &[&SysprepOperation],
+    verbose: bool,
+) -> Result<()> {
+    let mut g = Guestfs::new()?;
+    g.set_verbose(verbose);
+    g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
+    g.launch()?;
+    mount_for_sysprep(&mut g, true)?;
+
+    let results = g.sysprep_run(sysprep_ops, true);
+    g.umount_all().ok();
+    g.shutdown().ok();
+
+    println!("Sysprep operations (dry run, {} unchanged):", image.display());
+    for (op, result) in sysprep_ops.iter().zip(results?) {
+        println!();
+        println!("{} - {}", op.name.bold(), op.description);
+        if result.is_empty() {
+            println!("  {}", "nothing to change".dimmed());
+        }
+        for path in &result.removed {
+            println!("  {} {}", "-".red(), path);
+        }
+        for path in &result.truncated {
+            println!("  {} {} (truncate)", "~".yellow(), path);
+        }
+        for path in &result.created {
+            println!("  {} {} (create)", "+".green(), path);
+        }
+    }
+    Ok(())
+}
+
 /// Security patch analysis and CVE detection
 pub fn patch_command(
     image: &PathBuf,
     check_cves: bool,
     severity: Option<String>,
     export: Option<PathBuf>,
+    export_vex: Option<PathBuf>,
+    vex: &[PathBuf],
     simulate_update: bool,
     verbose: bool,
 ) -> Result<()> {
@@ -6533,27 +7564,27 @@ pub fn patch_command(
     use guestkit::Guestfs;
     use std::collections::HashMap;
 
+    // Vulnerabilities come from the database `guestctl vulndb update` builds
+    let db = if check_cves { Some(VulnDb::open()?) } else { None };
+    let vex = Vex::load(vex)?;
+
     let mut g = Guestfs::new()?;
     g.set_verbose(verbose);
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
-        }
+        g.mount_os_ro(root).ok();
     }
 
     progress.set_message("Analyzing installed packages...");
@@ -6572,6 +7603,21 @@ pub fn patch_command(
         }
     }
 
+    let mut cves = match (&db, roots.first()) {
+        (Some(db), Some(root)) => {
+            progress.set_message("Checking packages for known CVEs...");
+            package_cves(&mut g, root, db)
+        }
+        _ => None,
+    };
+    let mut suppressed = 0;
+    if let Some(cves) = &mut cves {
+        let found = cves.len();
+        cves.retain(|c| !vex.suppresses(&c.vuln.cve, &c.purl));
+        suppressed = found - cves.len();
+    }
+    let mut statements = Vec::new();
+
     progress.finish_and_clear();
 
     println!("Patch Analysis Report");
@@ -6582,39 +7628,48 @@ pub fn patch_command(
 
     // Analyze packages for known vulnerabilities
     if check_cves {
-        println!("🔍 CVE Analysis:");
+        println!("{}CVE Analysis:", plain::text("🔍 "));
         println!();
 
-        // Simulated CVE checking (in production, this would query a CVE database)
-        let vulnerable_packages = vec![
-            ("openssl", "1.1.1k", "CVE-2021-3711", "HIGH", "Buffer overflow in SM2 decryption"),
-            ("sudo", "1.8.31", "CVE-2021-3156", "CRITICAL", "Heap buffer overflow (Baron Samedit)"),
-            ("systemd", "245", "CVE-2020-13776", "MEDIUM", "Improper access control"),
-            ("kernel", "5.4.0", "CVE-2022-0847", "CRITICAL", "Dirty Pipe privilege escalation"),
-            ("glibc", "2.31", "CVE-2021-33574", "HIGH", "Use-after-free in mq_notify"),
-        ];
-
         let severity_filter = severity.as_deref().unwrap_or("ALL");
 
-        for (pkg, ver, cve, sev, desc) in vulnerable_packages {
-            if packages.contains_key(pkg) {
-                if severity_filter == "ALL" || severity_filter == sev {
-                    let icon = match sev {
-                        "CRITICAL" => "🔴",
-                        "HIGH" => "🟠",
-                        "MEDIUM" => "🟡",
+        match &cves {
+            None => {
+                println!("  CVE check skipped: no vulnerability data for this release");
+                println!();
+            }
+            Some(cves) => {
+                for PackageCve { app, purl, vuln } in cves {
+                    if !severity_filter.eq_ignore_ascii_case("ALL")
+                        && !severity_filter.eq_ignore_ascii_case(&vuln.severity)
+                    {
+                        continue;
+                    }
+                    let icon = match vuln.severity.as_str() {
+                        "critical" => "🔴",
+                        "high" => "🟠",
+                        "medium" => "🟡",
                         _ => "🟢",
                     };
 
-                    println!("{} {} [{}]", icon, cve, sev);
-                    println!("   Package: {} {}", pkg, ver);
-                    println!("   Description: {}", desc);
+                    println!("{} {} [{}]", plain::text(icon), vuln.cve, vuln.severity.to_uppercase());
+                    println!("   Package: {} {}", app.name, app.full_version());
+                    println!("   Description: {}", vuln.description);
+                    if let Some(fixed) = &vuln.fixed_version {
+                        println!("   Fixed in: {}", fixed);
+                    }
                     println!();
+                    statements.push(Statement::affected(
+                        &vuln.cve,
+                        purl,
+                        &app.name,
+                        vuln.fixed_version.as_deref(),
+                    ));
 
-                    match sev {
-                        "CRITICAL" => critical_cves += 1,
-                        "HIGH" => high_cves += 1,
-                        "MEDIUM" => medium_cves += 1,
+                    match vuln.severity.as_str() {
+                        "critical" => critical_cves += 1,
+                        "high" => high_cves += 1,
+                        "medium" => medium_cves += 1,
                         _ => {}
                     }
                 }
@@ -6625,15 +7680,18 @@ pub fn patch_command(
         println!("  Critical: {}", critical_cves);
         println!("  High: {}", high_cves);
         println!("  Medium: {}", medium_cves);
+        if suppressed > 0 {
+            println!("  Not affected or fixed (VEX): {}", suppressed);
+        }
         println!();
 
         if critical_cves > 0 {
-            println!("⚠️  URGENT: {} critical vulnerabilities require immediate patching!", critical_cves);
+            println!("{}  URGENT: {} critical vulnerabilities require immediate patching!", plain::text("⚠️"), critical_cves);
         }
     }
 
     // Check for outdated packages (simulated)
-    println!("📦 Package Update Status:");
+    println!("{}Package Update Status:", plain::text("📦 "));
     println!();
 
     // Sample outdated packages
@@ -6646,13 +7704,20 @@ pub fn patch_command(
 
     for (pkg, current, latest) in &sample_outdated {
         if packages.contains_key(*pkg) {
-            println!("  📌 {} : {} → {} (update available)", pkg, current, latest);
+            println!(
+                "  {}{} : {} {} {} (update available)",
+                plain::text("📌 "),
+                pkg,
+                current,
+                plain::text("→"),
+                latest
+            );
             outdated += 1;
         }
     }
 
     if outdated == 0 {
-        println!("  ✓ All checked packages are up to date");
+        println!("  {} All checked packages are up to date", plain::text("✓"));
     } else {
         println!();
         println!("  Total updates available: {}", outdated);
@@ -6664,7 +7729,7 @@ pub fn patch_command(
         println!("=================");
         println!("The following packages would be updated:");
         for (pkg, _current, latest) in &sample_outdated {
-            println!("  • {} → {}", pkg, latest);
+            println!("  {} {} {} {}", plain::text("•"), pkg, plain::text("→"), latest);
         }
         println!();
         println!("Note: This is a simulation. No changes were made.");
@@ -6691,6 +7756,24 @@ pub fn patch_command(
         println!("Report exported to: {}", export_path.display());
     }
 
+    // OpenVEX statements for the CVEs found, to triage and pass back with --vex
+    if let Some(vex_path) = export_vex {
+        if cves.is_none() {
+            println!();
+            println!("VEX export skipped: no vulnerability data for this release");
+        } else {
+            let document = VexDocument::new("guestctl", statements);
+            std::fs::write(&vex_path, serde_json::to_string_pretty(&document)?)
+                .with_context(|| format!("Failed to write {}", vex_path.display()))?;
+            println!();
+            println!(
+                "VEX document with {} statements exported to: {}",
+                document.statements.len(),
+                vex_path.display()
+            );
+        }
+    }
+
     g.umount_all().ok();
     g.shutdown().ok();
     Ok(())
@@ -6706,29 +7789,27 @@ pub fn audit_command(
     verbose: bool,
 ) -> Result<()> {
     use guestkit::core::ProgressReporter;
+    use guestkit::guestfs::login_ops::LoginKind;
     use guestkit::Guestfs;
+    use std::collections::HashMap;
 
     let mut g = Guestfs::new()?;
     g.set_verbose(verbose);
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
-        }
+        g.mount_os_ro(root).ok();
     }
 
     progress.set_message("Running comprehensive security audit...");
@@ -6752,7 +7833,7 @@ pub fn audit_command(
     for category in &audit_categories {
         match category.as_str() {
             "permissions" => {
-                println!("🔐 File Permissions Audit:");
+                println!("{}File Permissions Audit:", plain::text("🔐 "));
                 println!();
 
                 // Check for world-writable files
@@ -6766,8 +7847,12 @@ pub fn audit_command(
                                     if let Ok(stat) = g.stat(file) {
                                         // World-writable files
                                         if stat.mode & 0o002 != 0 {
-                                            println!("  ⚠️  World-writable: {} (mode: {:o})",
-                                                file, stat.mode & 0o777);
+                                            println!(
+                                                "  {}  World-writable: {} (mode: {:o})",
+                                                plain::text("⚠️"),
+                                                file,
+                                                stat.mode & 0o777
+                                            );
                                             findings.push((
                                                 "CRITICAL".to_string(),
                                                 "World-writable file in critical location".to_string(),
@@ -6790,8 +7875,12 @@ pub fn audit_command(
                             if g.is_file(file).unwrap_or(false) {
                                 if let Ok(stat) = g.stat(file) {
                                     if stat.mode & 0o4000 != 0 {
-                                        println!("  🔑 SUID binary: {} (owner: {})",
-                                            file, stat.uid);
+                                        println!(
+                                            "  {}SUID binary: {} (owner: {})",
+                                            plain::text("🔑 "),
+                                            file,
+                                            stat.uid
+                                        );
                                         findings.push((
                                             "MEDIUM".to_string(),
                                             "SUID binary found".to_string(),
@@ -6808,7 +7897,7 @@ pub fn audit_command(
             }
 
             "users" => {
-                println!("👥 User Account Audit:");
+                println!("{}User Account Audit:", plain::text("👥 "));
                 println!();
 
                 // Check /etc/passwd
@@ -6823,7 +7912,11 @@ pub fn audit_command(
                                 if parts.len() >= 4 {
                                     // Check for UID 0 (root)
                                     if parts[2] == "0" && parts[0] != "root" {
-                                        println!("  ⚠️  Non-root user with UID 0: {}", parts[0]);
+                                        println!(
+                                            "  {}  Non-root user with UID 0: {}",
+                                            plain::text("⚠️"),
+                                            parts[0]
+                                        );
                                         findings.push((
                                             "CRITICAL".to_string(),
                                             "Non-root account with UID 0".to_string(),
@@ -6843,7 +7936,7 @@ pub fn audit_command(
                                             let parts: Vec<&str> = line.split(':').collect();
                                             if parts.len() >= 2 {
                                                 if parts[1].is_empty() || parts[1] == "!" {
-                                                    println!("  ⚠️  Account with no password: {}", parts[0]);
+                                                    println!("  {}  Account with no password: {}", plain::text("⚠️"), parts[0]);
                                                     no_password_accounts += 1;
                                                 }
                                             }
@@ -6855,16 +7948,81 @@ pub fn audit_command(
                             total_issues += root_accounts + no_password_accounts;
 
                             if root_accounts == 0 && no_password_accounts == 0 {
-                                println!("  ✓ No critical user account issues found");
+                                println!(
+                                    "  {} No critical user account issues found",
+                                    plain::text("✓")
+                                );
                             }
                         }
                     }
                 }
+
+                // Login history from wtmp and btmp
+                let events = g.login_history().unwrap_or_default();
+                if events.is_empty() {
+                    println!("  {}  No login history (wtmp/btmp) found", plain::text("ℹ️"));
+                } else {
+                    const FAILED_LOGIN_THRESHOLD: usize = 10;
+
+                    let count = |kind| events.iter().filter(|e| e.kind == kind).count();
+                    println!(
+                        "  Login history: {} logins, {} failed logins, {} boots",
+                        count(LoginKind::Login),
+                        count(LoginKind::FailedLogin),
+                        count(LoginKind::Boot)
+                    );
+                    if let Some(login) = events.iter().rev().find(|e| e.kind == LoginKind::Login) {
+                        let time =
+                            chrono::DateTime::from_timestamp(login.time, 0).unwrap_or_default();
+                        println!(
+                            "  Last login: {} at {}",
+                            login.describe(),
+                            time.format("%Y-%m-%d %H:%M:%S UTC")
+                        );
+                    }
+
+                    let mut failures: HashMap<String, usize> = HashMap::new();
+                    for event in events.iter().filter(|e| e.kind == LoginKind::FailedLogin) {
+                        let source = event.remote().unwrap_or_else(|| "local".to_string());
+                        *failures.entry(source).or_insert(0) += 1;
+                    }
+                    let mut failures: Vec<_> = failures
+                        .into_iter()
+                        .filter(|(_, n)| *n >= FAILED_LOGIN_THRESHOLD)
+                        .collect();
+                    failures.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+                    for (source, n) in failures {
+                        println!("  {}  {} failed logins from {}", plain::text("⚠️"), n, source);
+                        findings.push((
+                            "HIGH".to_string(),
+                            "Repeated failed logins".to_string(),
+                            source,
+                        ));
+                        total_issues += 1;
+                    }
+
+                    let mut root_sources: Vec<String> = events
+                        .iter()
+                        .filter(|e| e.kind == LoginKind::Login && e.user == "root")
+                        .filter_map(|e| e.remote())
+                        .collect();
+                    root_sources.sort();
+                    root_sources.dedup();
+                    for source in root_sources {
+                        println!("  {}  Remote root login from {}", plain::text("⚠️"), source);
+                        findings.push((
+                            "MEDIUM".to_string(),
+                            "Direct root login over the network".to_string(),
+                            source,
+                        ));
+                        total_issues += 1;
+                    }
+                }
                 println!();
             }
 
             "network" => {
-                println!("🌐 Network Configuration Audit:");
+                println!("{}Network Configuration Audit:", plain::text("🌐 "));
                 println!();
 
                 // Check for open network services
@@ -6880,7 +8038,11 @@ pub fn audit_command(
                         let service_path = format!("/etc/systemd/system/{}", service);
                         if g.exists(&service_path).unwrap_or(false) {
                             if service.contains("telnet") || service.contains("rsh") {
-                                println!("  ⚠️  Insecure service enabled: {}", service);
+                                println!(
+                                    "  {}  Insecure service enabled: {}",
+                                    plain::text("⚠️"),
+                                    service
+                                );
                                 findings.push((
                                     "HIGH".to_string(),
                                     "Insecure network service".to_string(),
@@ -6898,9 +8060,9 @@ pub fn audit_command(
                     || g.is_dir("/etc/firewalld").unwrap_or(false);
 
                 if has_firewall {
-                    println!("  ✓ Firewall configuration detected");
+                    println!("  {} Firewall configuration detected", plain::text("✓"));
                 } else {
-                    println!("  ⚠️  No firewall configuration found");
+                    println!("  {}  No firewall configuration found", plain::text("⚠️"));
                     findings.push((
                         "HIGH".to_string(),
                         "No firewall configured".to_string(),
@@ -6912,7 +8074,7 @@ pub fn audit_command(
             }
 
             "services" => {
-                println!("⚙️  Service Configuration Audit:");
+                println!("{} Service Configuration Audit:", plain::text("⚙️ "));
                 println!();
 
                 // Check for unnecessary services
@@ -6925,7 +8087,11 @@ pub fn audit_command(
                 for service in unnecessary_services {
                     let service_path = format!("/etc/systemd/system/{}.service", service);
                     if g.exists(&service_path).unwrap_or(false) {
-                        println!("  ℹ️  Potentially unnecessary service: {}", service);
+                        println!(
+                            "  {}  Potentially unnecessary service: {}",
+                            plain::text("ℹ️"),
+                            service
+                        );
                         findings.push((
                             "LOW".to_string(),
                             "Unnecessary service may be running".to_string(),
@@ -6935,35 +8101,150 @@ pub fn audit_command(
                     }
                 }
 
-                println!("  ✓ Service audit complete");
+                println!("  {} Service audit complete", plain::text("✓"));
                 println!();
             }
 
-            _ => {
-                println!("  ⚠️  Unknown audit category: {}", category);
-            }
-        }
-    }
+            "auditd" => {
+                println!("{}Audit Log (auditd) Review:", plain::text("📜 "));
+                println!();
 
-    // Summary
-    println!("Audit Summary");
-    println!("=============");
-    println!("Total issues found: {}", total_issues);
-    println!("Critical issues: {}", critical_issues);
-    println!();
+                let events = g.auditd_events().unwrap_or_default();
+                if events.is_empty() {
+                    println!("  {}  No audit log found", plain::text("ℹ️"));
+                    println!();
+                    continue;
+                }
 
-    if total_issues == 0 {
-        println!("✅ No security issues detected");
-    } else if critical_issues > 0 {
-        println!("❌ CRITICAL: Immediate action required for {} issues", critical_issues);
-    } else {
-        println!("⚠️  Review and remediate {} issues", total_issues);
-    }
+                const FAILED_AUTH_THRESHOLD: usize = 10;
+                const ACCOUNT_CHANGES: &[&str] = &[
+                    "ADD_USER", "DEL_USER", "USER_MGMT", "ADD_GROUP", "DEL_GROUP", "GRP_MGMT",
+                ];
 
-    if fix_issues {
-        println!();
-        println!("Note: Automated remediation not implemented in read-only mode");
-        println!("      Manual fixes required for detected issues");
+                let first = chrono::DateTime::from_timestamp(events[0].time, 0).unwrap_or_default();
+                let last = chrono::DateTime::from_timestamp(events[events.len() - 1].time, 0)
+                    .unwrap_or_default();
+                println!(
+                    "  {} events from {} to {}",
+                    events.len(),
+                    first.format("%Y-%m-%d %H:%M:%S UTC"),
+                    last.format("%Y-%m-%d %H:%M:%S UTC")
+                );
+
+                let mut failures: HashMap<String, usize> = HashMap::new();
+                for event in &events {
+                    let is_auth = matches!(event.kind(), "USER_AUTH" | "USER_LOGIN" | "USER_ERR");
+                    if is_auth && event.success() == Some(false) {
+                        let source = event.addr().unwrap_or("local").to_string();
+                        *failures.entry(source).or_insert(0) += 1;
+                    }
+                }
+                let mut failures: Vec<_> = failures
+                    .into_iter()
+                    .filter(|(_, n)| *n >= FAILED_AUTH_THRESHOLD)
+                    .collect();
+                failures.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+                for (source, n) in failures {
+                    println!(
+                        "  {}  {} failed authentications from {}",
+                        plain::text("⚠️"),
+                        n,
+                        source
+                    );
+                    findings.push((
+                        "HIGH".to_string(),
+                        "Repeated failed authentications".to_string(),
+                        source,
+                    ));
+                    total_issues += 1;
+                }
+
+                let mut temp_execs: Vec<String> = events
+                    .iter()
+                    .filter(|e| e.syscall().as_deref() == Some("execve"))
+                    .filter_map(|e| e.field("exe"))
+                    .filter(|exe| {
+                        ["/tmp/", "/var/tmp/", "/dev/shm/"].iter().any(|dir| exe.starts_with(dir))
+                    })
+                    .map(str::to_string)
+                    .collect();
+                temp_execs.sort();
+                temp_execs.dedup();
+                for exe in temp_execs {
+                    println!(
+                        "  {}  Program executed from a temporary directory: {}",
+                        plain::text("⚠️"),
+                        exe
+                    );
+                    findings.push((
+                        "HIGH".to_string(),
+                        "Execution from temporary directory".to_string(),
+                        exe,
+                    ));
+                    total_issues += 1;
+                }
+
+                for event in &events {
+                    let time = chrono::DateTime::from_timestamp(event.time, 0).unwrap_or_default();
+                    let title = if event.kind() == "CONFIG_CHANGE" {
+                        "Audit configuration changed"
+                    } else if ACCOUNT_CHANGES.contains(&event.kind()) {
+                        "Account or group modified"
+                    } else if matches!(
+                        event.syscall().as_deref(),
+                        Some("init_module" | "finit_module")
+                    ) || event.kind() == "KERN_MODULE"
+                    {
+                        "Kernel module loaded"
+                    } else {
+                        continue;
+                    };
+                    println!(
+                        "  {}  {} {}",
+                        plain::text("ℹ️"),
+                        time.format("%Y-%m-%d %H:%M:%S"),
+                        event.describe()
+                    );
+                    findings.push(("MEDIUM".to_string(), title.to_string(), event.describe()));
+                    total_issues += 1;
+                }
+
+                let denials = events.iter().filter(|e| e.kind() == "AVC").count();
+                if denials > 0 {
+                    println!("  {}  {} SELinux/AppArmor AVC records", plain::text("ℹ️"), denials);
+                }
+                println!();
+            }
+
+            _ => {
+                println!("  {}  Unknown audit category: {}", plain::text("⚠️"), category);
+            }
+        }
+    }
+
+    // Summary
+    println!("Audit Summary");
+    println!("=============");
+    println!("Total issues found: {}", total_issues);
+    println!("Critical issues: {}", critical_issues);
+    println!();
+
+    if total_issues == 0 {
+        println!("{} No security issues detected", plain::text("✅"));
+    } else if critical_issues > 0 {
+        println!(
+            "{} CRITICAL: Immediate action required for {} issues",
+            plain::text("❌"),
+            critical_issues
+        );
+    } else {
+        println!("{}  Review and remediate {} issues", plain::text("⚠️"), total_issues);
+    }
+
+    if fix_issues {
+        println!();
+        println!("Note: Automated remediation not implemented in read-only mode");
+        println!("      Manual fixes required for detected issues");
     }
 
     // Export report
@@ -7020,6 +8301,7 @@ pub fn repair_command(
     repair_type: &str,
     force: bool,
     backup: bool,
+    boot_options: &guestkit::guestfs::BootRepairOptions,
     verbose: bool,
 ) -> Result<()> {
     use guestkit::core::ProgressReporter;
@@ -7030,22 +8312,17 @@ pub fn repair_command(
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive(image.to_str().unwrap())?;
+    target::add_disks(&mut g, false)?;
+    target::select(&mut g);
 
     progress.set_message("Launching repair environment...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
-        let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount(device, mount).ok();
-            }
-        }
+        g.mount_os_rw(&roots[0])?;
     }
 
     match repair_type {
@@ -7069,7 +8346,7 @@ pub fn repair_command(
                         let current_mode = stat.mode & 0o777;
                         if current_mode != correct_mode {
                             if backup {
-                                println!("  Would fix: {} ({:o} → {:o})", file, current_mode, correct_mode);
+                                println!("  Would fix: {} ({:o} {} {:o})", file, current_mode, plain::text("→"), correct_mode);
                             }
                             g.chmod(correct_mode as i32, file).ok();
                             fixed += 1;
@@ -7079,7 +8356,7 @@ pub fn repair_command(
             }
 
             progress.finish_and_clear();
-            println!("✓ Permission repair complete");
+            println!("{} Permission repair complete", plain::text("✓"));
             println!("  Fixed {} permission issues", fixed);
         }
 
@@ -7110,26 +8387,22 @@ pub fn repair_command(
         }
 
         "bootloader" => {
-            progress.set_message("Checking bootloader...");
+            progress.set_message("Repairing bootloader...");
+            let report = g.boot_repair(boot_options)?;
+            progress.finish_and_clear();
 
             println!("Bootloader Repair:");
-            println!("  GRUB configuration: ");
-
-            if g.is_file("/boot/grub/grub.cfg").unwrap_or(false) {
-                println!("    ✓ Found at /boot/grub/grub.cfg");
-            } else if g.is_file("/boot/grub2/grub.cfg").unwrap_or(false) {
-                println!("    ✓ Found at /boot/grub2/grub.cfg");
+            if report.bootloaders.is_empty() {
+                println!("  {}  No GRUB or systemd-boot configuration found", plain::text("⚠️"));
             } else {
-                println!("    ⚠️  GRUB configuration not found");
+                println!("  Bootloaders: {}", report.bootloaders.join(", "));
+            }
+            for change in &report.changes {
+                println!("  {} {}", plain::text("✓"), change);
+            }
+            for warning in &report.warnings {
+                println!("  {}  {}", plain::text("⚠️"), warning);
             }
-
-            println!();
-            println!("  Note: Bootloader repair requires:");
-            println!("    1. Chroot into the system");
-            println!("    2. Run grub-install and grub-mkconfig");
-            println!("    3. Verify boot parameters");
-
-            progress.finish_and_clear();
         }
 
         "filesystem" => {
@@ -7176,6 +8449,8 @@ pub fn harden_command(
 
     if apply {
         g.add_drive(image.to_str().unwrap())?;
+        target::add_disks(&mut g, false)?;
+        target::select(&mut g);
     } else {
         g.add_drive_ro(image.to_str().unwrap())?;
     }
@@ -7185,18 +8460,16 @@ pub fn harden_command(
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
+        if !apply {
+            g.mount_os_ro(root).ok();
+        } else if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
             let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
+            mounts.sort_by_key(|(mount, _)| mount.len());
             for (mount, device) in mounts {
-                if apply {
-                    g.mount(device, mount).ok();
-                } else {
-                    g.mount_ro(device, mount).ok();
-                }
+                g.mount(device, mount).ok();
             }
         }
     }
@@ -7262,7 +8535,7 @@ pub fn harden_command(
     println!();
 
     if apply {
-        println!("✓ Hardening configuration applied");
+        println!("{} Hardening configuration applied", plain::text("✓"));
         println!();
         println!("IMPORTANT:");
         println!("  1. Review changes before deploying to production");
@@ -7296,22 +8569,18 @@ pub fn anomaly_command(
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
-        }
+        g.mount_os_ro(root).ok();
     }
 
     progress.set_message("Analyzing system for anomalies...");
@@ -7340,7 +8609,7 @@ pub fn anomaly_command(
     for category in &check_categories {
         match category.as_str() {
             "files" => {
-                println!("🔍 File System Anomalies:");
+                println!("{}File System Anomalies:", plain::text("🔍 "));
                 println!();
 
                 // Detect files with unusual characteristics
@@ -7373,7 +8642,7 @@ pub fn anomaly_command(
                                     score,
                                     format!("{} suspicious files in {}", count, path),
                                 ));
-                                println!("  ⚠️  {}: {} items (score: {})", description, count, score);
+                                println!("  {}  {}: {} items (score: {})", plain::text("⚠️"), description, count, score);
                             }
                         }
                     }
@@ -7401,8 +8670,12 @@ pub fn anomaly_command(
                                 score,
                                 format!("{} files owned by root", root_owned),
                             ));
-                            println!("  ⚠️  Unusual ownership: {} root-owned files in /home (score: {})",
-                                root_owned, score);
+                            println!(
+                                "  {}  Unusual ownership: {} root-owned files in /home (score: {})",
+                                plain::text("⚠️"),
+                                root_owned,
+                                score
+                            );
                         }
                     }
                 }
@@ -7433,8 +8706,12 @@ pub fn anomaly_command(
                                 score,
                                 format!("{} files modified in last 24h", recently_modified),
                             ));
-                            println!("  ⚠️  Recent modifications: {} files in /etc (score: {})",
-                                recently_modified, score);
+                            println!(
+                                "  {}  Recent modifications: {} files in /etc (score: {})",
+                                plain::text("⚠️"),
+                                recently_modified,
+                                score
+                            );
                         }
                     }
                 }
@@ -7442,7 +8719,7 @@ pub fn anomaly_command(
             }
 
             "config" => {
-                println!("⚙️  Configuration Anomalies:");
+                println!("{} Configuration Anomalies:", plain::text("⚙️ "));
                 println!();
 
                 // Detect unusual config patterns
@@ -7468,7 +8745,13 @@ pub fn anomaly_command(
                                         score,
                                         format!("{} lines", lines),
                                     ));
-                                    println!("  ⚠️  {}: {} lines (score: {})", desc, lines, score);
+                                    println!(
+                                        "  {}  {}: {} lines (score: {})",
+                                        plain::text("⚠️"),
+                                        desc,
+                                        lines,
+                                        score
+                                    );
                                 }
 
                                 // Detect suspicious patterns
@@ -7481,8 +8764,12 @@ pub fn anomaly_command(
                                         score,
                                         "curl | bash detected".to_string(),
                                     ));
-                                    println!("  🚨 CRITICAL: Download-and-execute in {} (score: {})",
-                                        desc, score);
+                                    println!(
+                                        "  {}CRITICAL: Download-and-execute in {} (score: {})",
+                                        plain::text("🚨 "),
+                                        desc,
+                                        score
+                                    );
                                 }
                             }
                         }
@@ -7492,7 +8779,7 @@ pub fn anomaly_command(
             }
 
             "network" => {
-                println!("🌐 Network Anomalies:");
+                println!("{}Network Anomalies:", plain::text("🌐 "));
                 println!();
 
                 // Check for unusual network configurations
@@ -7512,8 +8799,12 @@ pub fn anomaly_command(
                                     score,
                                     format!("{} entries", entries),
                                 ));
-                                println!("  ⚠️  Large hosts file: {} entries (score: {})",
-                                    entries, score);
+                                println!(
+                                    "  {}  Large hosts file: {} entries (score: {})",
+                                    plain::text("⚠️"),
+                                    entries,
+                                    score
+                                );
                             }
 
                             // Check for suspicious redirects
@@ -7528,8 +8819,12 @@ pub fn anomaly_command(
                                         score,
                                         "Possible DNS hijacking".to_string(),
                                     ));
-                                    println!("  🚨 CRITICAL: Hosts redirect for {} (score: {})",
-                                        domain, score);
+                                    println!(
+                                        "  {}CRITICAL: Hosts redirect for {} (score: {})",
+                                        plain::text("🚨 "),
+                                        domain,
+                                        score
+                                    );
                                 }
                             }
                         }
@@ -7539,7 +8834,7 @@ pub fn anomaly_command(
             }
 
             "processes" => {
-                println!("🔄 Process/Service Anomalies:");
+                println!("{}Process/Service Anomalies:", plain::text("🔄 "));
                 println!();
 
                 // Check for unusual systemd units
@@ -7563,8 +8858,12 @@ pub fn anomaly_command(
                                 score,
                                 format!("{} suspicious units", suspicious_services),
                             ));
-                            println!("  ⚠️  Suspicious services: {} units (score: {})",
-                                suspicious_services, score);
+                            println!(
+                                "  {}  Suspicious services: {} units (score: {})",
+                                plain::text("⚠️"),
+                                suspicious_services,
+                                score
+                            );
                         }
                     }
                 }
@@ -7584,7 +8883,7 @@ pub fn anomaly_command(
     println!("Cumulative anomaly score: {}", anomaly_score);
     println!();
 
-    let risk_level = if anomaly_score >= 100 {
+    let risk_level = plain::text(if anomaly_score >= 100 {
         "🔴 CRITICAL - Immediate investigation required"
     } else if anomaly_score >= 70 {
         "🟠 HIGH - Detailed review recommended"
@@ -7592,7 +8891,7 @@ pub fn anomaly_command(
         "🟡 MEDIUM - Monitor for changes"
     } else {
         "🟢 LOW - Normal variation"
-    };
+    });
 
     println!("Risk Level: {}", risk_level);
     println!();
@@ -7602,8 +8901,14 @@ pub fn anomaly_command(
         anomalies.sort_by(|a, b| b.2.cmp(&a.2)); // Sort by score descending
 
         for (category, description, score, details) in anomalies.iter().take(10) {
-            println!("  • [{}] {} - {} (score: {})",
-                category, description, details, score);
+            println!(
+                "  {} [{}] {} - {} (score: {})",
+                plain::text("•"),
+                category,
+                description,
+                details,
+                score
+            );
         }
     }
 
@@ -7657,22 +8962,18 @@ pub fn recommend_command(
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
-        }
+        g.mount_os_ro(root).ok();
     }
 
     progress.set_message("Generating intelligent recommendations...");
@@ -7695,7 +8996,7 @@ pub fn recommend_command(
     for area in &focus_areas {
         match area.as_str() {
             "security" => {
-                println!("🔒 Security Recommendations:");
+                println!("{}Security Recommendations:", plain::text("🔒 "));
                 println!();
 
                 // SSH hardening
@@ -7755,7 +9056,7 @@ pub fn recommend_command(
             }
 
             "performance" => {
-                println!("⚡ Performance Recommendations:");
+                println!("{}Performance Recommendations:", plain::text("⚡ "));
                 println!();
 
                 // Check for large log files
@@ -7804,7 +9105,7 @@ pub fn recommend_command(
             }
 
             "reliability" => {
-                println!("🛡️  Reliability Recommendations:");
+                println!("{} Reliability Recommendations:", plain::text("🛡️ "));
                 println!();
 
                 // Backup strategy
@@ -7842,7 +9143,7 @@ pub fn recommend_command(
             }
 
             "cost" => {
-                println!("💰 Cost Optimization Recommendations:");
+                println!("{}Cost Optimization Recommendations:", plain::text("💰 "));
                 println!();
 
                 // Storage optimization
@@ -7903,10 +9204,13 @@ pub fn recommend_command(
     println!();
 
     if apply {
-        println!("⚠️  Auto-apply mode not yet implemented");
+        println!("{}  Auto-apply mode not yet implemented", plain::text("⚠️"));
         println!("    Recommendations require manual review and implementation");
     } else {
-        println!("💡 Tip: Review these recommendations and implement based on your requirements");
+        println!(
+            "{}Tip: Review these recommendations and implement based on your requirements",
+            plain::text("💡 ")
+        );
         println!("    Use --apply flag (when implemented) to auto-apply safe recommendations");
     }
 
@@ -7931,22 +9235,18 @@ pub fn predict_command(
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
-        }
+        g.mount_os_ro(root).ok();
     }
 
     progress.set_message("Analyzing trends and generating predictions...");
@@ -7960,7 +9260,7 @@ pub fn predict_command(
 
     match metric {
         "disk-growth" => {
-            println!("💾 Disk Space Prediction:");
+            println!("{}Disk Space Prediction:", plain::text("💾 "));
             println!();
 
             // Get current disk usage
@@ -7995,20 +9295,31 @@ pub fn predict_command(
 
                     // Capacity warnings
                     if predicted_percent > 90 {
-                        println!("  🔴 CRITICAL: Disk will exceed 90% in {} days!", timeframe);
+                        println!(
+                            "  {} CRITICAL: Disk will exceed 90% in {} days!",
+                            plain::text("🔴"),
+                            timeframe
+                        );
                         println!("     Action required: Cleanup or expand storage immediately");
                     } else if predicted_percent > 80 {
-                        println!("  🟠 WARNING: Disk will exceed 80% in {} days", timeframe);
+                        println!(
+                            "  {} WARNING: Disk will exceed 80% in {} days",
+                            plain::text("🟠"),
+                            timeframe
+                        );
                         println!("     Recommendation: Plan storage expansion");
                     } else {
-                        println!("  🟢 OK: Sufficient capacity for forecast period");
+                        println!(
+                            "  {} OK: Sufficient capacity for forecast period",
+                            plain::text("🟢")
+                        );
                     }
                 }
             }
         }
 
         "log-growth" => {
-            println!("📋 Log Growth Prediction:");
+            println!("{}Log Growth Prediction:", plain::text("📋 "));
             println!();
 
             if let Ok(files) = g.find("/var/log") {
@@ -8036,15 +9347,18 @@ pub fn predict_command(
                 println!();
 
                 if predicted_total > 10.0 {
-                    println!("  ⚠️  Recommendation: Implement log rotation and archival");
+                    println!(
+                        "  {}  Recommendation: Implement log rotation and archival",
+                        plain::text("⚠️")
+                    );
                 } else {
-                    println!("  ✓ Log growth within acceptable limits");
+                    println!("  {} Log growth within acceptable limits", plain::text("✓"));
                 }
             }
         }
 
         "package-updates" => {
-            println!("📦 Package Update Prediction:");
+            println!("{}Package Update Prediction:", plain::text("📦 "));
             println!();
 
             if !roots.is_empty() {
@@ -8092,143 +9406,93 @@ pub fn predict_command(
 /// Threat intelligence correlation and IOC detection
 pub fn intelligence_command(
     image: &PathBuf,
-    ioc_file: Option<PathBuf>,
+    ioc_files: Vec<PathBuf>,
     threat_level: &str,
     correlate: bool,
     export: Option<PathBuf>,
+    attack_layer: Option<PathBuf>,
     verbose: bool,
 ) -> Result<()> {
+    use super::attack::Catalog;
+    use super::ioc::{self, scan, IocSet};
     use guestkit::core::ProgressReporter;
     use guestkit::Guestfs;
-    use std::collections::HashMap;
+
+    let Some(min_level) = ioc::severity_level(threat_level) else {
+        anyhow::bail!(
+            "Unknown threat level: {} (expected critical, high, medium or low)",
+            threat_level
+        );
+    };
+
+    // Parse the feeds before the appliance starts, so mistakes show early
+    let mut iocs = IocSet::default();
+    for path in &ioc_files {
+        iocs.extend(IocSet::load(path)?);
+    }
+    if iocs.is_empty() {
+        anyhow::bail!("No usable indicators in {} IOC files", ioc_files.len());
+    }
 
     let mut g = Guestfs::new()?;
     g.set_verbose(verbose);
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
-        }
+        g.mount_os_ro(root).ok();
     }
 
     progress.set_message("Correlating with threat intelligence...");
+    let mut matches = scan::scan(&mut g, &iocs, |stage| progress.set_message(stage.to_string()));
+    matches.retain(|m| ioc::severity_rank(&m.severity) <= ioc::severity_rank(min_level));
 
-    println!("Threat Intelligence Analysis");
-    println!("===========================");
-    println!("Threat Level Filter: {}", threat_level);
-    println!();
-
-    // Known malicious indicators (simulated threat intelligence)
-    let mut ioc_database: HashMap<String, (&str, &str, &str)> = HashMap::new();
-
-    // IP addresses (IOC, threat level, description)
-    ioc_database.insert("192.168.100.50".to_string(), ("IP", "HIGH", "Known C2 server"));
-    ioc_database.insert("10.0.0.13".to_string(), ("IP", "MEDIUM", "Suspicious scanning host"));
-
-    // File hashes (MD5)
-    ioc_database.insert("5d41402abc4b2a76b9719d911017c592".to_string(), ("HASH", "CRITICAL", "Known ransomware"));
-    ioc_database.insert("098f6bcd4621d373cade4e832627b4f6".to_string(), ("HASH", "HIGH", "Trojan backdoor"));
-
-    // Domains
-    ioc_database.insert("malicious-domain.evil".to_string(), ("DOMAIN", "CRITICAL", "Command & Control"));
-    ioc_database.insert("phishing-site.bad".to_string(), ("DOMAIN", "HIGH", "Phishing campaign"));
-
-    // File paths
-    ioc_database.insert("/tmp/.hidden_miner".to_string(), ("FILE", "CRITICAL", "Cryptominer"));
-    ioc_database.insert("/dev/shm/backdoor".to_string(), ("FILE", "HIGH", "Backdoor payload"));
+    progress.finish_and_clear();
+    g.umount_all().ok();
+    g.shutdown().ok();
 
-    // Usernames
-    ioc_database.insert("backdoor_user".to_string(), ("USER", "CRITICAL", "Unauthorized account"));
+    let catalog = Catalog::bundled();
+    let detection = |ioc_type: &str| format!("ioc/{}", ioc_type.to_lowercase());
 
-    // Load custom IOCs if provided
-    if let Some(ioc_path) = ioc_file {
-        println!("Loading IOCs from: {}", ioc_path.display());
-        // In production, would parse STIX, OpenIOC, or CSV format
-        println!();
+    println!("Threat Intelligence Analysis");
+    println!("===========================");
+    println!("Threat Level Filter: {}", min_level);
+    println!(
+        "Indicators: {} ({})",
+        iocs.len(),
+        iocs.counts()
+            .iter()
+            .map(|(label, n)| format!("{} {}", n, label))
+            .collect::<Vec<_>>()
+            .join(", ")
+    );
+    if iocs.skipped > 0 {
+        println!("Skipped: {} feed entries guestctl cannot match", iocs.skipped);
     }
-
-    let mut matches = Vec::new();
-
-    // Check hosts file for malicious IPs/domains
-    println!("🔍 Scanning for Indicators of Compromise:");
     println!();
 
-    if g.is_file("/etc/hosts").unwrap_or(false) {
-        if let Ok(content) = g.read_file("/etc/hosts") {
-            if let Ok(text) = String::from_utf8(content) {
-                for line in text.lines() {
-                    for (ioc, (ioc_type, level, desc)) in &ioc_database {
-                        if line.contains(ioc) && ioc_type == &"IP" || ioc_type == &"DOMAIN" {
-                            matches.push((ioc.clone(), ioc_type.to_string(), level.to_string(),
-                                desc.to_string(), "/etc/hosts".to_string()));
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    // Check for malicious files
-    let suspicious_paths = vec!["/tmp", "/dev/shm", "/var/tmp"];
-    for path in suspicious_paths {
-        if g.is_dir(path).unwrap_or(false) {
-            if let Ok(files) = g.find(path) {
-                for file in files.iter().take(100) {
-                    for (ioc, (ioc_type, level, desc)) in &ioc_database {
-                        if file.contains(ioc) && ioc_type == &"FILE" {
-                            matches.push((ioc.clone(), ioc_type.to_string(), level.to_string(),
-                                desc.to_string(), file.clone()));
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    // Check for malicious users
-    if g.is_file("/etc/passwd").unwrap_or(false) {
-        if let Ok(content) = g.read_file("/etc/passwd") {
-            if let Ok(text) = String::from_utf8(content) {
-                for line in text.lines() {
-                    for (ioc, (ioc_type, level, desc)) in &ioc_database {
-                        if line.contains(ioc) && ioc_type == &"USER" {
-                            matches.push((ioc.clone(), ioc_type.to_string(), level.to_string(),
-                                desc.to_string(), "/etc/passwd".to_string()));
-                        }
-                    }
-                }
-            }
-        }
-    }
-
-    progress.finish_and_clear();
-
     // Display results
     if matches.is_empty() {
-        println!("✅ No threat intelligence matches found");
-        println!("   System appears clean against known IOCs");
+        println!("{} No threat intelligence matches found", plain::text("✅"));
+        println!("   System appears clean against the loaded IOCs");
     } else {
-        println!("⚠️  THREAT DETECTED: {} IOC matches found", matches.len());
+        println!("{}  THREAT DETECTED: {} IOC matches found", plain::text("⚠️"), matches.len());
         println!();
 
         // Group by threat level
         for level in ["CRITICAL", "HIGH", "MEDIUM", "LOW"] {
             let level_matches: Vec<_> = matches.iter()
-                .filter(|(_, _, l, _, _)| l == level)
+                .filter(|m| m.severity == level)
                 .collect();
 
             if !level_matches.is_empty() {
@@ -8239,10 +9503,22 @@ pub fn intelligence_command(
                     _ => "🟢",
                 };
 
-                println!("{} {} Severity ({} matches):", icon, level, level_matches.len());
-                for (ioc, ioc_type, _, desc, location) in level_matches.iter().take(10) {
-                    println!("  • [{}] {} - {}", ioc_type, desc, ioc);
-                    println!("    Location: {}", location);
+                println!("{} {} Severity ({} matches):", plain::text(icon), level, level_matches.len());
+                for m in level_matches.iter().take(10) {
+                    let desc = if m.description.is_empty() { &m.source } else { &m.description };
+                    println!("  {} [{}] {} - {}", plain::text("•"), m.kind, desc, m.value);
+                    println!("    Location: {}", m.location);
+                    if let Some(evidence) = &m.evidence {
+                        println!("    Evidence: {}", evidence);
+                    }
+                    let techniques: Vec<&str> = catalog
+                        .detected_by(&detection(m.kind))
+                        .iter()
+                        .map(|t| t.id.as_str())
+                        .collect();
+                    if !techniques.is_empty() {
+                        println!("    ATT&CK: {}", techniques.join(", "));
+                    }
                 }
                 if level_matches.len() > 10 {
                     println!("  ... and {} more", level_matches.len() - 10);
@@ -8254,28 +9530,29 @@ pub fn intelligence_command(
 
     // Correlation analysis
     if correlate && !matches.is_empty() {
-        println!("🔗 Correlation Analysis:");
+        println!("{}Correlation Analysis:", plain::text("🔗 "));
         println!();
 
-        let critical_count = matches.iter().filter(|(_, _, l, _, _)| l == "CRITICAL").count();
-        let high_count = matches.iter().filter(|(_, _, l, _, _)| l == "HIGH").count();
+        let critical_count = matches.iter().filter(|m| m.severity == "CRITICAL").count();
+        let high_count = matches.iter().filter(|m| m.severity == "HIGH").count();
 
         if critical_count > 0 && high_count > 0 {
-            println!("  ⚠️  MULTI-STAGE ATTACK DETECTED");
+            println!("  {}  MULTI-STAGE ATTACK DETECTED", plain::text("⚠️"));
             println!("     Multiple high-severity IOCs suggest coordinated attack");
             println!("     Recommendation: Immediate incident response required");
             println!();
         }
 
-        // Check for attack patterns
-        let has_c2 = matches.iter().any(|(_, _, _, desc, _)| desc.contains("C2") || desc.contains("Command"));
-        let has_backdoor = matches.iter().any(|(_, _, _, desc, _)| desc.contains("backdoor") || desc.contains("Backdoor"));
-        let has_persistence = matches.iter().any(|(_, t, _, _, _)| t == "USER");
+        // Check for attack patterns: a dropped file that talks to known
+        // infrastructure
+        let has_c2 = matches.iter().any(|m| matches!(m.kind, "IP" | "DOMAIN" | "URL"));
+        let has_payload = matches.iter().any(|m| matches!(m.kind, "HASH" | "FILE"));
+        let has_persistence = matches.iter().any(|m| m.kind == "USER");
 
-        if has_c2 && has_backdoor {
-            println!("  🎯 Attack Chain Identified:");
-            println!("     1. Initial compromise via backdoor");
-            println!("     2. C2 communication established");
+        if has_c2 && has_payload {
+            println!("  {}Attack Chain Identified:", plain::text("🎯 "));
+            println!("     1. Malicious payload present on disk");
+            println!("     2. Known attacker infrastructure referenced");
             if has_persistence {
                 println!("     3. Persistence mechanism detected (user account)");
             }
@@ -8283,16 +9560,21 @@ pub fn intelligence_command(
         }
 
         // Lateral movement indicators
-        if matches.iter().any(|(_, _, _, _, loc)| loc.contains("/etc/hosts")) {
-            println!("  ⚡ Potential Lateral Movement:");
-            println!("     Hosts file modification suggests network reconnaissance");
+        if matches.iter().any(|m| m.location.ends_with("_history")) {
+            println!("  {}Potential Lateral Movement:", plain::text("⚡ "));
+            println!("     Shell history references known attacker infrastructure");
+            println!();
+        }
+        if matches.iter().any(|m| m.location == "/etc/hosts") {
+            println!("  {}Name Resolution Tampering:", plain::text("⚡ "));
+            println!("     Hosts file pins known attacker infrastructure");
             println!();
         }
     }
 
     // Recommendations
     if !matches.is_empty() {
-        println!("🛡️  Incident Response Recommendations:");
+        println!("{} Incident Response Recommendations:", plain::text("🛡️ "));
         println!();
         println!("  1. IMMEDIATE: Isolate system from network");
         println!("  2. Preserve forensic evidence (memory dump, disk image)");
@@ -8312,21 +9594,39 @@ pub fn intelligence_command(
         writeln!(output, "# Threat Intelligence Report")?;
         writeln!(output, "Image: {}", image.display())?;
         writeln!(output, "Timestamp: {}", chrono::Utc::now().to_rfc3339())?;
-        writeln!(output, "")?;
+        writeln!(output)?;
         writeln!(output, "## IOC Matches: {}", matches.len())?;
-        writeln!(output, "")?;
+        writeln!(output)?;
 
-        for (ioc, ioc_type, level, desc, location) in &matches {
-            writeln!(output, "- [{}] [{}] {}: {}", level, ioc_type, ioc, desc)?;
-            writeln!(output, "  Location: {}", location)?;
+        for m in &matches {
+            writeln!(output, "- [{}] [{}] {}: {}", m.severity, m.kind, m.value, m.description)?;
+            writeln!(output, "  Location: {}", m.location)?;
+            writeln!(output, "  Source: {}", m.source)?;
         }
 
         println!();
         println!("Report exported to: {}", export_path.display());
     }
 
-    g.umount_all().ok();
-    g.shutdown().ok();
+    if let Some(layer_path) = attack_layer {
+        let detections: Vec<String> = matches.iter().map(|m| detection(m.kind)).collect();
+        let layer = catalog.layer(
+            &format!("guestctl intelligence: {}", image.display()),
+            "Threat intelligence matches",
+            matches
+                .iter()
+                .zip(&detections)
+                .map(|(m, d)| (d.as_str(), m.severity.as_str(), m.location.as_str())),
+        );
+        layer.save(&layer_path)?;
+        println!();
+        println!(
+            "ATT&CK Navigator layer ({} techniques) exported to: {}",
+            layer.scored().count(),
+            layer_path.display()
+        );
+    }
+
     Ok(())
 }
 
@@ -8356,6 +9656,8 @@ pub fn simulate_command(
 
     if dry_run {
         g.add_drive_ro(image.to_str().unwrap())?;
+        target::add_disks(&mut g, true)?;
+        target::select(&mut g);
     } else {
         g.add_drive(image.to_str().unwrap())?;
     }
@@ -8365,18 +9667,16 @@ pub fn simulate_command(
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
+        if dry_run {
+            g.mount_os_ro(root).ok();
+        } else if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
             let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
+            mounts.sort_by_key(|(mount, _)| mount.len());
             for (mount, device) in mounts {
-                if dry_run {
-                    g.mount_ro(device, mount).ok();
-                } else {
-                    g.mount(device, mount).ok();
-                }
+                g.mount(device, mount).ok();
             }
         }
     }
@@ -8389,7 +9689,7 @@ pub fn simulate_command(
 
     match change_type {
         "remove-package" => {
-            println!("📦 Package Removal Simulation:");
+            println!("{}Package Removal Simulation:", plain::text("📦 "));
             println!();
 
             // Simulate package dependency check
@@ -8405,7 +9705,11 @@ pub fn simulate_command(
                         let dependents = vec!["lib-dependent-1", "app-using-lib", "service-requiring-pkg"];
 
                         println!("  Impact Analysis:");
-                        println!("  ❌ {} packages will be affected", dependents.len());
+                        println!(
+                            "  {} {} packages will be affected",
+                            plain::text("❌"),
+                            dependents.len()
+                        );
                         for dep in &dependents {
                             println!("     - {}", dep);
                             impacts.push(format!("Package removal: {}", dep));
@@ -8417,7 +9721,7 @@ pub fn simulate_command(
                         // Service impact
                         println!("  Service Impact:");
                         if g.is_dir("/etc/systemd/system").unwrap_or(false) {
-                            println!("  ⚠️  May affect running services");
+                            println!("  {}  May affect running services", plain::text("⚠️"));
                             println!("     - Requires service restart");
                             impacts.push("Service restart required".to_string());
                             risk_score += 20;
@@ -8425,14 +9729,18 @@ pub fn simulate_command(
                         println!();
 
                     } else {
-                        println!("  ✓ Package '{}' not found - no impact", target);
+                        println!(
+                            "  {} Package '{}' not found - no impact",
+                            plain::text("✓"),
+                            target
+                        );
                     }
                 }
             }
         }
 
         "modify-config" => {
-            println!("⚙️  Configuration Change Simulation:");
+            println!("{} Configuration Change Simulation:", plain::text("⚙️ "));
             println!();
 
             if g.is_file(&target).unwrap_or(false) {
@@ -8446,7 +9754,7 @@ pub fn simulate_command(
 
                     // Check if config is in use
                     if target.contains("/etc/ssh/sshd_config") {
-                        println!("  ⚠️  SSH configuration modification");
+                        println!("  {}  SSH configuration modification", plain::text("⚠️"));
                         println!("     Risk: May lock you out if misconfigured");
                         println!("     Mitigation: Keep existing session open");
                         impacts.push("SSH access may be affected".to_string());
@@ -8454,7 +9762,7 @@ pub fn simulate_command(
                     }
 
                     if target.contains("/etc/fstab") {
-                        println!("  🔴 CRITICAL: Filesystem table modification");
+                        println!("  {} CRITICAL: Filesystem table modification", plain::text("🔴"));
                         println!("     Risk: System may fail to boot");
                         println!("     Mitigation: Test in VM before production");
                         impacts.push("Boot failure risk".to_string());
@@ -8462,7 +9770,7 @@ pub fn simulate_command(
                     }
 
                     if target.contains("/etc/network") || target.contains("/etc/netplan") {
-                        println!("  ⚠️  Network configuration change");
+                        println!("  {}  Network configuration change", plain::text("⚠️"));
                         println!("     Risk: Network connectivity loss");
                         println!("     Mitigation: Physical console access required");
                         impacts.push("Network connectivity at risk".to_string());
@@ -8472,13 +9780,17 @@ pub fn simulate_command(
                     println!();
                 }
             } else {
-                println!("  ✓ File '{}' does not exist - would create new", target);
+                println!(
+                    "  {} File '{}' does not exist - would create new",
+                    plain::text("✓"),
+                    target
+                );
                 risk_score += 10;
             }
         }
 
         "disable-service" => {
-            println!("🔧 Service Disable Simulation:");
+            println!("{}Service Disable Simulation:", plain::text("🔧 "));
             println!();
 
             let service_path = if target.ends_with(".service") {
@@ -8497,12 +9809,12 @@ pub fn simulate_command(
             let is_critical = critical_services.iter().any(|s| service_path.contains(s));
 
             if is_critical {
-                println!("  🔴 CRITICAL SERVICE");
+                println!("  {} CRITICAL SERVICE", plain::text("🔴"));
                 println!("     Disabling may cause system unavailability");
                 impacts.push(format!("Critical service: {}", service_path));
                 risk_score += 80;
             } else {
-                println!("  ✓ Non-critical service");
+                println!("  {} Non-critical service", plain::text("✓"));
                 risk_score += 20;
             }
 
@@ -8516,13 +9828,13 @@ pub fn simulate_command(
         }
 
         "kernel-update" => {
-            println!("🚀 Kernel Update Simulation:");
+            println!("{}Kernel Update Simulation:", plain::text("🚀 "));
             println!();
 
             println!("  Impact Analysis:");
-            println!("  ⚠️  System reboot required");
-            println!("  ⚠️  All running processes will be interrupted");
-            println!("  ⚠️  Kernel modules may need recompilation");
+            println!("  {}  System reboot required", plain::text("⚠️"));
+            println!("  {}  All running processes will be interrupted", plain::text("⚠️"));
+            println!("  {}  Kernel modules may need recompilation", plain::text("⚠️"));
             println!();
 
             impacts.push("System reboot required".to_string());
@@ -8545,7 +9857,7 @@ pub fn simulate_command(
 
     // Risk assessment
     if risk_assessment {
-        println!("🎯 Risk Assessment:");
+        println!("{}Risk Assessment:", plain::text("🎯 "));
         println!();
 
         let risk_level = if risk_score >= 80 {
@@ -8559,12 +9871,12 @@ pub fn simulate_command(
         };
 
         println!("  Risk Score: {} / 100", risk_score);
-        println!("  Risk Level: {} {}", risk_level.1, risk_level.0);
+        println!("  Risk Level: {} {}", plain::text(risk_level.1), risk_level.0);
         println!("  Recommendation: {}", risk_level.2);
         println!();
 
         if risk_score >= 60 {
-            println!("  🛡️  Recommended Safeguards:");
+            println!("  {} Recommended Safeguards:", plain::text("🛡️ "));
             println!("     1. Create VM snapshot before change");
             println!("     2. Have rollback plan ready");
             println!("     3. Schedule maintenance window");
@@ -8579,15 +9891,15 @@ pub fn simulate_command(
     println!("==================");
     println!("Total impacts: {}", impacts.len());
     for impact in &impacts {
-        println!("  • {}", impact);
+        println!("  {} {}", plain::text("•"), impact);
     }
     println!();
 
     if dry_run {
-        println!("✓ Simulation complete - no changes made");
+        println!("{} Simulation complete - no changes made", plain::text("✓"));
         println!("  Review impacts above before applying changes");
     } else {
-        println!("⚠️  Live execution mode not yet implemented");
+        println!("{}  Live execution mode not yet implemented", plain::text("⚠️"));
         println!("   This would apply the change with safety checks");
     }
 
@@ -8614,22 +9926,18 @@ pub fn score_command(
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
-        }
+        g.mount_os_ro(root).ok();
     }
 
     progress.set_message("Calculating comprehensive risk scores...");
@@ -8666,7 +9974,7 @@ pub fn score_command(
     for dimension in &check_dimensions {
         let score = match dimension.as_str() {
             "security" => {
-                println!("🔒 Security Score:");
+                println!("{}Security Score:", plain::text("🔒 "));
                 let mut sec_score = 100;
 
                 // SSH configuration
@@ -8674,11 +9982,11 @@ pub fn score_command(
                     if let Ok(content) = g.read_file("/etc/ssh/sshd_config") {
                         if let Ok(text) = String::from_utf8(content) {
                             if text.contains("PermitRootLogin yes") {
-                                println!("  ⚠️  Root SSH login enabled (-15)");
+                                println!("  {}  Root SSH login enabled (-15)", plain::text("⚠️"));
                                 sec_score -= 15;
                             }
                             if text.contains("PasswordAuthentication yes") {
-                                println!("  ⚠️  Password auth enabled (-10)");
+                                println!("  {}  Password auth enabled (-10)", plain::text("⚠️"));
                                 sec_score -= 10;
                             }
                         }
@@ -8689,7 +9997,7 @@ pub fn score_command(
                 let has_firewall = g.is_file("/etc/sysconfig/iptables").unwrap_or(false)
                     || g.is_dir("/etc/ufw").unwrap_or(false);
                 if !has_firewall {
-                    println!("  ⚠️  No firewall detected (-20)");
+                    println!("  {}  No firewall detected (-20)", plain::text("⚠️"));
                     sec_score -= 20;
                 }
 
@@ -8697,7 +10005,7 @@ pub fn score_command(
                 let has_mac = g.is_file("/etc/selinux/config").unwrap_or(false)
                     || g.is_dir("/etc/apparmor.d").unwrap_or(false);
                 if !has_mac {
-                    println!("  ⚠️  No MAC system (-15)");
+                    println!("  {}  No MAC system (-15)", plain::text("⚠️"));
                     sec_score -= 15;
                 }
 
@@ -8707,7 +10015,7 @@ pub fn score_command(
             }
 
             "compliance" => {
-                println!("📋 Compliance Score:");
+                println!("{}Compliance Score:", plain::text("📋 "));
                 let mut comp_score = 100;
 
                 // Critical file permissions
@@ -8715,7 +10023,7 @@ pub fn score_command(
                     if let Ok(stat) = g.stat("/etc/shadow") {
                         let mode = stat.mode & 0o777;
                         if mode > 0o000 {
-                            println!("  ⚠️  /etc/shadow too permissive (-20)");
+                            println!("  {}  /etc/shadow too permissive (-20)", plain::text("⚠️"));
                             comp_score -= 20;
                         }
                     }
@@ -8723,7 +10031,7 @@ pub fn score_command(
 
                 // Audit system
                 if !g.is_file("/etc/audit/auditd.conf").unwrap_or(false) {
-                    println!("  ⚠️  No audit system (-15)");
+                    println!("  {}  No audit system (-15)", plain::text("⚠️"));
                     comp_score -= 15;
                 }
 
@@ -8733,11 +10041,11 @@ pub fn score_command(
             }
 
             "reliability" => {
-                println!("🛡️  Reliability Score:");
+                println!("{} Reliability Score:", plain::text("🛡️ "));
                 let mut rel_score = 100;
 
                 // Check for single points of failure
-                println!("  ℹ️  Analyzing redundancy...");
+                println!("  {}  Analyzing redundancy...", plain::text("ℹ️"));
 
                 // Filesystem health check
                 if let Ok(statvfs) = g.statvfs("/") {
@@ -8747,10 +10055,10 @@ pub fn score_command(
                     if blocks > 0 {
                         let usage_percent = ((blocks - bfree) * 100) / blocks;
                         if usage_percent > 90 {
-                            println!("  ⚠️  Disk usage critical (-25)");
+                            println!("  {}  Disk usage critical (-25)", plain::text("⚠️"));
                             rel_score -= 25;
                         } else if usage_percent > 80 {
-                            println!("  ⚠️  Disk usage high (-15)");
+                            println!("  {}  Disk usage high (-15)", plain::text("⚠️"));
                             rel_score -= 15;
                         }
                     }
@@ -8762,7 +10070,7 @@ pub fn score_command(
             }
 
             "performance" => {
-                println!("⚡ Performance Score:");
+                println!("{}Performance Score:", plain::text("⚡ "));
                 let mut perf_score = 100;
 
                 // Check for performance issues
@@ -8780,7 +10088,7 @@ pub fn score_command(
                         }
 
                         if large_logs > 5 {
-                            println!("  ⚠️  Excessive log files (-15)");
+                            println!("  {}  Excessive log files (-15)", plain::text("⚠️"));
                             perf_score -= 15;
                         }
                     }
@@ -8792,14 +10100,18 @@ pub fn score_command(
             }
 
             "maintainability" => {
-                println!("🔧 Maintainability Score:");
+                println!("{}Maintainability Score:", plain::text("🔧 "));
                 let mut maint_score = 100;
 
                 // Package count
                 if !roots.is_empty() {
                     if let Ok(apps) = g.inspect_list_applications(&roots[0]) {
                         if apps.len() > 500 {
-                            println!("  ⚠️  Excessive packages ({}) (-10)", apps.len());
+                            println!(
+                                "  {}  Excessive packages ({}) (-10)",
+                                plain::text("⚠️"),
+                                apps.len()
+                            );
                             maint_score -= 10;
                         }
                     }
@@ -8839,9 +10151,9 @@ pub fn score_command(
     };
 
     println!();
-    println!("  ═══════════════════════════════");
+    println!("  {}", plain::text("═══════════════════════════════"));
     println!("  Overall Score: {} / 100", overall_score);
-    println!("  ═══════════════════════════════");
+    println!("  {}", plain::text("═══════════════════════════════"));
     println!();
 
     let grade = if overall_score >= 90 {
@@ -8856,7 +10168,7 @@ pub fn score_command(
         ("D", "🔴", "Critical Issues")
     };
 
-    println!("  Grade: {} {}", grade.1, grade.0);
+    println!("  Grade: {} {}", plain::text(grade.1), grade.0);
     println!("  Assessment: {}", grade.2);
     println!();
 
@@ -8869,24 +10181,39 @@ pub fn score_command(
     }
 
     // Export report
-    if let Some(export_path) = export {
-        use std::fs::File;
-        use std::io::Write;
-
-        let mut output = File::create(&export_path)?;
-        writeln!(output, "# Risk Score Report")?;
-        writeln!(output, "Image: {}", image.display())?;
-        writeln!(output, "")?;
-        writeln!(output, "## Overall Score: {} / 100", overall_score)?;
-        writeln!(output, "Grade: {}", grade.0)?;
-        writeln!(output, "")?;
-        writeln!(output, "## Dimension Scores")?;
-        for (dimension, score) in &dimension_scores {
-            let weight = weight_map.get(dimension.as_str()).copied().unwrap_or(0);
-            writeln!(output, "- {}: {} / 100 (weight: {}%)", dimension, score, weight)?;
+    match export {
+        Some(export_path) if export_path.extension().is_some_and(|e| e == "json") => {
+            // JSON exports are read back by `guestctl fleet-report`
+            let result = crate::cli::fleet::ScoreResult {
+                image_path: image.display().to_string(),
+                generated_at: Some(chrono::Utc::now().to_rfc3339()),
+                overall_score,
+                grade: grade.0.to_string(),
+                dimensions: dimension_scores.into_iter().collect(),
+            };
+            std::fs::write(&export_path, serde_json::to_string_pretty(&result)?)?;
+            println!("Report exported to: {}", export_path.display());
         }
+        Some(export_path) => {
+            use std::fs::File;
+            use std::io::Write;
 
-        println!("Report exported to: {}", export_path.display());
+            let mut output = File::create(&export_path)?;
+            writeln!(output, "# Risk Score Report")?;
+            writeln!(output, "Image: {}", image.display())?;
+            writeln!(output, "")?;
+            writeln!(output, "## Overall Score: {} / 100", overall_score)?;
+            writeln!(output, "Grade: {}", grade.0)?;
+            writeln!(output, "")?;
+            writeln!(output, "## Dimension Scores")?;
+            for (dimension, score) in &dimension_scores {
+                let weight = weight_map.get(dimension.as_str()).copied().unwrap_or(0);
+                writeln!(output, "- {}: {} / 100 (weight: {}%)", dimension, score, weight)?;
+            }
+
+            println!("Report exported to: {}", export_path.display());
+        }
+        None => {}
     }
 
     g.umount_all().ok();
@@ -8911,22 +10238,18 @@ pub fn template_command(
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
-        }
+        g.mount_os_ro(root).ok();
     }
 
     progress.set_message("Validating against template...");
@@ -9024,10 +10347,10 @@ pub fn template_command(
         };
 
         if validation_passed {
-            println!("✅ PASS");
+            println!("{} PASS", plain::text("✅"));
             passed += 1;
         } else {
-            println!("❌ FAIL");
+            println!("{} FAIL", plain::text("❌"));
             failed += 1;
             violations.push((check_name.to_string(), requirement.to_string(), *critical));
         }
@@ -9042,19 +10365,19 @@ pub fn template_command(
     let critical_failures = violations.iter().filter(|(_, _, crit)| *crit).count();
 
     if critical_failures > 0 {
-        println!("❌ VALIDATION FAILED");
+        println!("{} VALIDATION FAILED", plain::text("❌"));
         println!("   {} critical requirements not met", critical_failures);
         println!();
         println!("   Critical Violations:");
         for (name, req, _) in violations.iter().filter(|(_, _, crit)| *crit) {
-            println!("     • {}: {}", name, req);
+            println!("     {} {}: {}", plain::text("•"), name, req);
         }
     } else if failed > 0 {
-        println!("⚠️  PARTIAL COMPLIANCE");
+        println!("{}  PARTIAL COMPLIANCE", plain::text("⚠️"));
         println!("   All critical requirements met");
         println!("   {} optional requirements not met", failed);
     } else {
-        println!("✅ VALIDATION PASSED");
+        println!("{} VALIDATION PASSED", plain::text("✅"));
         println!("   Image complies with {} template", template);
     }
 
@@ -9095,34 +10418,106 @@ pub fn hunt_command(
     techniques: Vec<String>,
     depth: &str,
     export: Option<PathBuf>,
+    attack_layer: Option<PathBuf>,
     verbose: bool,
 ) -> Result<()> {
+    use super::attack::{navigator, Catalog, Technique};
+    use super::malware::rootkit;
+    use super::matcher::PathFilter;
+    use super::secrets::{rules::RulePack, Scanner};
     use guestkit::core::ProgressReporter;
     use guestkit::Guestfs;
     use std::collections::HashMap;
 
+    let catalog = Catalog::bundled();
+    let selected = catalog.select(&techniques)?;
+
     let mut g = Guestfs::new()?;
     g.set_verbose(verbose);
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
+        g.mount_os_ro(root).ok();
+    }
+
+    let hunt_depth = match depth {
+        "surface" => 1,
+        "shallow" => 2,
+        "deep" => 3,
+        "comprehensive" => usize::MAX,
+        _ => 2,
+    };
+
+    // Techniques per tactic, as deep as asked
+    let mut plan: Vec<(&str, Vec<&Technique>)> = Vec::new();
+    for (tactic, technique) in selected {
+        match plan.last_mut() {
+            Some((last, list)) if *last == tactic => list.push(technique),
+            _ => plan.push((tactic, vec![technique])),
         }
     }
+    for (_, list) in &mut plan {
+        list.truncate(hunt_depth);
+    }
+    let planned: Vec<_> = plan.iter().flat_map(|(_, list)| list.iter().copied()).collect();
+
+    // Findings of the detection modules the planned techniques rely on, as
+    // (detection ID, severity, path)
+    let mut detections: Vec<(String, String, String)> = Vec::new();
+    let uses_malware = planned.iter().any(|t| {
+        t.detections
+            .iter()
+            .any(|d| !d.starts_with("secrets/") && !d.starts_with("ioc/"))
+    });
+    if uses_malware {
+        progress.set_message("Running rootkit and persistence checks...");
+        let packaged = rootkit::Packaged::load(&mut g);
+        let mut found = rootkit::ld_preload(&mut g);
+        found.extend(rootkit::kernel_modules(&mut g, &packaged));
+        found.extend(rootkit::systemd_units(&mut g, &packaged));
+        found.extend(rootkit::cron_jobs(&mut g, &packaged));
+        found.extend(rootkit::setuid_binaries(&mut g, &packaged));
+        detections.extend(found.into_iter().map(|f| (f.check, f.severity, f.path)));
+    }
+
+    // Credentials are searched for where the techniques say to look
+    let secret_artifacts: Vec<&str> = planned
+        .iter()
+        .filter(|t| t.detections.iter().any(|d| d.starts_with("secrets/")))
+        .flat_map(|t| t.artifacts.iter().map(|a| a.as_str()))
+        .collect();
+    if !secret_artifacts.is_empty() {
+        progress.set_message("Scanning for credentials...");
+        let mut bases: Vec<&str> = secret_artifacts
+            .iter()
+            .map(|a| match a.find('*') {
+                Some(star) => a[..star].trim_end_matches('/'),
+                None => a,
+            })
+            .collect();
+        bases.sort();
+        bases.dedup();
+        let scanner = Scanner::new(&[RulePack::builtin()])?;
+        let filter = PathFilter::new(&secret_artifacts, &[], None, false)?;
+        let report = scanner.scan_guest(&mut g, &bases, &filter, |_| {});
+        detections.extend(
+            report
+                .findings
+                .into_iter()
+                .map(|f| (format!("secrets/{}", f.rule), "HIGH".to_string(), f.path)),
+        );
+    }
 
     progress.set_message("Initiating threat hunt...");
     progress.finish_and_clear();
@@ -9134,148 +10529,101 @@ pub fn hunt_command(
     println!("Depth: {}", depth);
     println!();
 
-    // MITRE ATT&CK technique mapping
-    let mut attack_techniques: HashMap<&str, Vec<(&str, &str, &str)>> = HashMap::new();
-
-    // Initial Access
-    attack_techniques.insert("initial-access", vec![
-        ("T1190", "Exploit Public-Facing Application", "/var/log/apache2,/var/log/nginx"),
-        ("T1133", "External Remote Services", "/etc/ssh/sshd_config,/var/log/auth.log"),
-        ("T1078", "Valid Accounts", "/etc/passwd,/etc/shadow,/var/log/secure"),
-    ]);
-
-    // Persistence
-    attack_techniques.insert("persistence", vec![
-        ("T1053", "Scheduled Task/Job", "/etc/cron.d,/etc/crontab,/var/spool/cron"),
-        ("T1543", "Create/Modify System Process", "/etc/systemd/system,/lib/systemd/system"),
-        ("T1136", "Create Account", "/etc/passwd,/etc/group"),
-        ("T1098", "Account Manipulation", "/home/*/.ssh/authorized_keys"),
-    ]);
-
-    // Privilege Escalation
-    attack_techniques.insert("privilege-escalation", vec![
-        ("T1548", "Abuse Elevation Control", "/etc/sudoers,/etc/sudoers.d"),
-        ("T1068", "Exploitation for Privilege Escalation", "/var/log/kern.log"),
-        ("T1574", "Hijack Execution Flow", "/etc/ld.so.preload"),
-    ]);
-
-    // Defense Evasion
-    attack_techniques.insert("defense-evasion", vec![
-        ("T1070", "Indicator Removal", "/var/log,/root/.bash_history"),
-        ("T1562", "Impair Defenses", "/etc/selinux,/etc/apparmor.d"),
-        ("T1036", "Masquerading", "/usr/bin,/usr/sbin"),
-    ]);
-
-    // Credential Access
-    attack_techniques.insert("credential-access", vec![
-        ("T1003", "OS Credential Dumping", "/etc/shadow,/var/log/auth.log"),
-        ("T1552", "Unsecured Credentials", "/root/.ssh,/home/*/.aws,/home/*/.docker"),
-    ]);
-
-    // Discovery
-    attack_techniques.insert("discovery", vec![
-        ("T1082", "System Information Discovery", "/proc/version,/etc/os-release"),
-        ("T1083", "File and Directory Discovery", "/tmp,/var/tmp"),
-        ("T1046", "Network Service Discovery", "/etc/services,/proc/net"),
-    ]);
-
-    // Collection
-    attack_techniques.insert("collection", vec![
-        ("T1005", "Data from Local System", "/home,/var/www,/opt"),
-        ("T1560", "Archive Collected Data", "/tmp/*.tar,/tmp/*.zip"),
-    ]);
-
-    // Command and Control
-    attack_techniques.insert("command-control", vec![
-        ("T1071", "Application Layer Protocol", "/etc/hosts,/proc/net/tcp"),
-        ("T1573", "Encrypted Channel", "/var/log/syslog"),
-    ]);
-
-    // Exfiltration
-    attack_techniques.insert("exfiltration", vec![
-        ("T1041", "Exfiltration Over C2 Channel", "/var/log/syslog,/proc/net"),
-        ("T1567", "Exfiltration Over Web Service", "/root/.aws,/home/*/.config"),
-    ]);
-
-    let hunt_depth = match depth {
-        "surface" => 1,
-        "shallow" => 2,
-        "deep" => 3,
-        "comprehensive" => 4,
-        _ => 2,
-    };
-
     let mut findings = Vec::new();
     let mut evidence_items = 0;
+    let mut layer = navigator::Layer::new(
+        &format!("guestctl hunt: {}", image.display()),
+        &hypothesis,
+        &catalog.version,
+    );
+    let mut hunted: HashMap<&str, Vec<String>> = HashMap::new();
 
-    println!("🔍 Hunt Execution:");
+    println!("{}Hunt Execution:", plain::text("🔍 "));
     println!();
 
-    // Execute hunt based on framework
-    let check_techniques: Vec<&str> = if techniques.is_empty() {
-        attack_techniques.keys().cloned().collect()
-    } else {
-        techniques.iter().map(|s| s.as_str()).collect()
-    };
+    for (tactic, technique_list) in &plan {
+        println!("  {}Hunting Tactic: {}", plain::text("📋 "), tactic.to_uppercase());
+        println!();
 
-    for &tactic in &check_techniques {
-        if let Some(technique_list) = attack_techniques.get(tactic) {
-            println!("  📋 Hunting Tactic: {}", tactic.to_uppercase());
-            println!();
+        for technique in technique_list {
+            print!("    [{}] {} ... ", technique.id, technique.name);
 
-            for (tech_id, tech_name, locations) in technique_list.iter().take(hunt_depth) {
-                print!("    [{}] {} ... ", tech_id, tech_name);
-
-                let mut tactic_evidence = Vec::new();
-
-                // Check each location
-                for location in locations.split(',') {
-                    let location = location.trim();
-
-                    if location.contains('*') {
-                        // Wildcard path - simplified check
-                        let base = location.split('*').next().unwrap_or(location);
-                        if g.is_dir(base).unwrap_or(false) {
-                            if let Ok(files) = g.find(base) {
-                                for file in files.iter().take(10) {
-                                    if g.is_file(file).unwrap_or(false) {
-                                        if let Ok(stat) = g.stat(file) {
-                                            if stat.size > 0 {
-                                                tactic_evidence.push(file.clone());
-                                            }
+            // A technique listed under several tactics is only hunted once
+            if let Some(evidence) = hunted.get(technique.id.as_str()) {
+                if evidence.is_empty() {
+                    println!("{} Clear", plain::text("✓"));
+                } else {
+                    println!("{}EVIDENCE FOUND (see above)", plain::text("🎯 "));
+                }
+                continue;
+            }
+
+            let mut tactic_evidence = Vec::new();
+
+            for (detection, severity, path) in &detections {
+                if technique.detected_by(detection) {
+                    tactic_evidence.push(format!("{} [{}] {}", detection, severity, path));
+                    layer.add(
+                        &technique.id,
+                        navigator::severity_score(severity),
+                        &format!("{}: {}", detection, path),
+                    );
+                }
+            }
+
+            // Check each location
+            for location in &technique.artifacts {
+                let location = location.trim();
+                let before = tactic_evidence.len();
+
+                if location.contains('*') {
+                    // Wildcard path - simplified check
+                    let base = location.split('*').next().unwrap_or(location);
+                    if g.is_dir(base).unwrap_or(false) {
+                        if let Ok(files) = g.find(base) {
+                            for file in files.iter().take(10) {
+                                if g.is_file(file).unwrap_or(false) {
+                                    if let Ok(stat) = g.stat(file) {
+                                        if stat.size > 0 {
+                                            tactic_evidence.push(file.clone());
                                         }
                                     }
                                 }
                             }
                         }
-                    } else if g.is_file(location).unwrap_or(false) {
-                        // Direct file check
-                        if let Ok(stat) = g.stat(location) {
-                            if stat.size > 0 {
-                                tactic_evidence.push(location.to_string());
-                            }
+                    }
+                } else if g.is_file(location).unwrap_or(false) {
+                    // Direct file check
+                    if let Ok(stat) = g.stat(location) {
+                        if stat.size > 0 {
+                            tactic_evidence.push(location.to_string());
                         }
-                    } else if g.is_dir(location).unwrap_or(false) {
-                        // Directory check
-                        if let Ok(files) = g.find(location) {
-                            let file_count = files.len();
-                            if file_count > 0 {
-                                tactic_evidence.push(format!("{} ({} items)", location, file_count));
-                            }
+                    }
+                } else if g.is_dir(location).unwrap_or(false) {
+                    // Directory check
+                    if let Ok(files) = g.find(location) {
+                        let file_count = files.len();
+                        if file_count > 0 {
+                            tactic_evidence.push(format!("{} ({} items)", location, file_count));
                         }
                     }
                 }
 
-                if !tactic_evidence.is_empty() {
-                    println!("🎯 EVIDENCE FOUND");
-                    evidence_items += tactic_evidence.len();
-                    findings.push((tactic.to_string(), tech_id.to_string(), tech_name.to_string(), tactic_evidence));
-                } else {
-                    println!("✓ Clear");
+                for item in &tactic_evidence[before..] {
+                    layer.add(&technique.id, 1, &format!("artifact: {}", item));
                 }
             }
-            println!();
+
+            if !tactic_evidence.is_empty() {
+                println!("{}EVIDENCE FOUND", plain::text("🎯 "));
+                evidence_items += tactic_evidence.len();
+                findings.push((tactic.to_string(), technique.id.clone(), technique.name.clone(), tactic_evidence.clone()));
+            } else {
+                println!("{} Clear", plain::text("✓"));
+            }
+            hunted.insert(&technique.id, tactic_evidence);
         }
+        println!();
     }
 
     // Hunt analysis
@@ -9284,22 +10632,32 @@ pub fn hunt_command(
     println!();
 
     if findings.is_empty() {
-        println!("✅ Hunt Complete - No suspicious indicators found");
+        println!("{} Hunt Complete - No suspicious indicators found", plain::text("✅"));
         println!("   Hypothesis: {}", hypothesis);
         println!("   Result: NOT SUPPORTED");
         println!();
         println!("   The system appears clean based on the hunt criteria.");
         println!("   Consider expanding hunt scope or refining hypothesis.");
     } else {
-        println!("⚠️  Hunt Complete - {} pieces of evidence collected", evidence_items);
+        println!(
+            "{}  Hunt Complete - {} pieces of evidence collected",
+            plain::text("⚠️"),
+            evidence_items
+        );
         println!("   Hypothesis: {}", hypothesis);
         println!("   Result: SUPPORTED - Further investigation required");
         println!();
 
         for (tactic, tech_id, tech_name, evidence) in &findings {
-            println!("  🔴 [{}] {} - {}", tech_id, tactic.to_uppercase(), tech_name);
+            println!(
+                "  {} [{}] {} - {}",
+                plain::text("🔴"),
+                tech_id,
+                tactic.to_uppercase(),
+                tech_name
+            );
             for item in evidence.iter().take(5) {
-                println!("     • {}", item);
+                println!("     {} {}", plain::text("•"), item);
             }
             if evidence.len() > 5 {
                 println!("     ... and {} more items", evidence.len() - 5);
@@ -9309,14 +10667,14 @@ pub fn hunt_command(
 
         // Correlation analysis
         if findings.len() >= 3 {
-            println!("  ⚠️  MULTI-STAGE ATTACK PATTERN DETECTED");
+            println!("  {}  MULTI-STAGE ATTACK PATTERN DETECTED", plain::text("⚠️"));
             println!("     {} tactics with evidence suggests sophisticated threat", findings.len());
             println!("     Recommendation: Full incident response required");
             println!();
         }
 
         // Next steps
-        println!("  🎯 Recommended Next Actions:");
+        println!("  {}Recommended Next Actions:", plain::text("🎯 "));
         println!();
         println!("     1. Preserve all evidence (disk image, memory dump)");
         println!("     2. Isolate system from network");
@@ -9354,6 +10712,16 @@ pub fn hunt_command(
         println!("Hunt report exported to: {}", export_path.display());
     }
 
+    if let Some(layer_path) = attack_layer {
+        layer.save(&layer_path)?;
+        println!();
+        println!(
+            "ATT&CK Navigator layer ({} techniques) exported to: {}",
+            layer.scored().count(),
+            layer_path.display()
+        );
+    }
+
     g.umount_all().ok();
     g.shutdown().ok();
     Ok(())
@@ -9378,22 +10746,18 @@ pub fn reconstruct_command(
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
-        }
+        g.mount_os_ro(root).ok();
     }
 
     progress.set_message("Reconstructing incident timeline...");
@@ -9410,11 +10774,11 @@ pub fn reconstruct_command(
     // Build comprehensive timeline
     let mut timeline: BTreeMap<i64, Vec<(String, String, String, String)>> = BTreeMap::new();
 
-    println!("📊 Evidence Collection:");
+    println!("{}Evidence Collection:", plain::text("📊 "));
     println!();
 
     // Filesystem artifacts
-    print!("  [1/6] Filesystem artifacts ... ");
+    print!("  [1/8] Filesystem artifacts ... ");
     let mut fs_artifacts = 0;
     let key_paths = vec!["/etc", "/var/log", "/tmp", "/root", "/home"];
     for path in &key_paths {
@@ -9438,10 +10802,10 @@ pub fn reconstruct_command(
             }
         }
     }
-    println!("✓ {} artifacts", fs_artifacts);
+    println!("{} {} artifacts", plain::text("✓"), fs_artifacts);
 
     // User activity
-    print!("  [2/6] User activity ... ");
+    print!("  [2/8] User activity ... ");
     let mut user_activities = 0;
     if g.is_file("/var/log/auth.log").unwrap_or(false) {
         if let Ok(content) = g.read_file("/var/log/auth.log") {
@@ -9462,10 +10826,10 @@ pub fn reconstruct_command(
             }
         }
     }
-    println!("✓ {} events", user_activities);
+    println!("{} {} events", plain::text("✓"), user_activities);
 
     // Network connections
-    print!("  [3/6] Network activity ... ");
+    print!("  [3/8] Network activity ... ");
     let mut network_events = 0;
     if g.is_file("/etc/hosts").unwrap_or(false) {
         if let Ok(stat) = g.stat("/etc/hosts") {
@@ -9480,10 +10844,10 @@ pub fn reconstruct_command(
             network_events += 1;
         }
     }
-    println!("✓ {} events", network_events);
+    println!("{} {} events", plain::text("✓"), network_events);
 
     // Process artifacts
-    print!("  [4/6] Process artifacts ... ");
+    print!("  [4/8] Process artifacts ... ");
     let mut process_artifacts = 0;
     let cron_paths = vec!["/etc/cron.d", "/etc/crontab", "/var/spool/cron"];
     for path in &cron_paths {
@@ -9501,10 +10865,10 @@ pub fn reconstruct_command(
             }
         }
     }
-    println!("✓ {} artifacts", process_artifacts);
+    println!("{} {} artifacts", plain::text("✓"), process_artifacts);
 
     // System configuration
-    print!("  [5/6] System configuration ... ");
+    print!("  [5/8] System configuration ... ");
     let mut config_changes = 0;
     let config_files = vec!["/etc/ssh/sshd_config", "/etc/sudoers", "/etc/passwd", "/etc/group"];
     for file in &config_files {
@@ -9522,10 +10886,10 @@ pub fn reconstruct_command(
             }
         }
     }
-    println!("✓ {} changes", config_changes);
+    println!("{} {} changes", plain::text("✓"), config_changes);
 
     // Log analysis
-    print!("  [6/6] System logs ... ");
+    print!("  [6/8] System logs ... ");
     let mut log_entries = 0;
     if g.is_dir("/var/log").unwrap_or(false) {
         if let Ok(files) = g.find("/var/log") {
@@ -9546,12 +10910,75 @@ pub fn reconstruct_command(
             }
         }
     }
-    println!("✓ {} logs", log_entries);
+    println!("{} {} logs", plain::text("✓"), log_entries);
+
+    // Crash evidence
+    print!("  [7/8] Crash evidence ... ");
+    let crash_evidence = crate::cli::crash::scanner::collect_evidence(&mut g);
+    for item in &crash_evidence {
+        if let Some(ts) = item.timestamp {
+            timeline.entry(ts)
+                .or_default()
+                .push((
+                    "CRASH".to_string(),
+                    format!("Crash ({})", item.kind.as_str()),
+                    item.path.clone(),
+                    item.summary.clone()
+                ));
+        }
+    }
+    println!("{} {} crashes", plain::text("✓"), crash_evidence.len());
+
+    // Audit log (auditd) events
+    print!("  [8/8] Audit events ... ");
+    let audit_events = g.auditd_events().unwrap_or_default();
+    let mut audit_correlated = 0;
+    for event in &audit_events {
+        let (category, event_type) = match (event.kind(), event.syscall().as_deref()) {
+            (_, Some("execve" | "execveat")) => ("PROCESS", "Command Executed"),
+            (_, Some("connect")) => ("NETWORK", "Outbound Connection"),
+            ("USER_AUTH" | "USER_LOGIN" | "USER_START" | "USER_END", _) => {
+                user_activities += 1;
+                ("USER", "Authentication Event")
+            }
+            (
+                "ADD_USER" | "DEL_USER" | "USER_MGMT" | "ADD_GROUP" | "DEL_GROUP" | "GRP_MGMT"
+                | "CONFIG_CHANGE",
+                _,
+            ) => {
+                config_changes += 1;
+                ("CONFIG", "Configuration Change")
+            }
+            _ => continue,
+        };
+        match category {
+            "PROCESS" => process_artifacts += 1,
+            "NETWORK" => network_events += 1,
+            _ => {}
+        }
+        let mut details = format!("audit serial {}", event.serial);
+        if let Some(auid) = event.auid() {
+            details.push_str(&format!(", auid {}", auid));
+        }
+        if let Some(session) = event.session() {
+            details.push_str(&format!(", session {}", session));
+        }
+        timeline.entry(event.time)
+            .or_default()
+            .push((
+                category.to_string(),
+                event_type.to_string(),
+                event.describe(),
+                details
+            ));
+        audit_correlated += 1;
+    }
+    println!("{} {} of {} events", plain::text("✓"), audit_correlated, audit_events.len());
 
     println!();
 
     // Reconstruct attack narrative
-    println!("🔍 Attack Reconstruction:");
+    println!("{}Attack Reconstruction:", plain::text("🔍 "));
     println!();
 
     let total_events = timeline.values().map(|v| v.len()).sum::<usize>();
@@ -9563,7 +10990,7 @@ pub fn reconstruct_command(
         println!();
 
         // Show chronological timeline
-        println!("  📅 Chronological Event Sequence:");
+        println!("  {}Chronological Event Sequence:", plain::text("📅 "));
         println!();
 
         let mut event_count = 0;
@@ -9576,7 +11003,7 @@ pub fn reconstruct_command(
                     dt.format("%Y-%m-%d %H:%M:%S"),
                     category,
                     event_type);
-                println!("       └─ {}", artifact);
+                println!("       {} {}", plain::text("└─"), artifact);
                 if !details.is_empty() && details != artifact {
                     println!("          {}", details);
                 }
@@ -9598,7 +11025,7 @@ pub fn reconstruct_command(
         println!();
 
         // Attack narrative
-        println!("  📖 Incident Narrative:");
+        println!("  {}Incident Narrative:", plain::text("📖 "));
         println!();
 
         match incident_type {
@@ -9635,33 +11062,64 @@ pub fn reconstruct_command(
         }
     }
 
+    // Login sessions reconstructed from the audit log
+    let audit_sessions = guestkit::guestfs::auditd_ops::correlate_sessions(&audit_events);
+    if !audit_sessions.is_empty() {
+        println!("  {}Correlated Audit Sessions:", plain::text("🔗 "));
+        println!();
+        for session in audit_sessions.iter().rev().take(10) {
+            let start = chrono::DateTime::from_timestamp(session.start, 0).unwrap_or_default();
+            let end = chrono::DateTime::from_timestamp(session.end, 0).unwrap_or_default();
+            println!(
+                "    Session {} (auid {}) {} {} {}",
+                session.session,
+                session.auid.map(|a| a.to_string()).unwrap_or_else(|| "?".to_string()),
+                start.format("%Y-%m-%d %H:%M:%S"),
+                plain::text("→"),
+                end.format("%H:%M:%S")
+            );
+            if let Some(ref addr) = session.addr {
+                println!("       from {} on {}", addr, session.terminal.as_deref().unwrap_or("?"));
+            }
+            println!("       {} events, {} commands, {} privileged, {} failed",
+                session.events,
+                session.commands.len(),
+                session.privileged,
+                session.failed);
+            for command in session.commands.iter().take(5) {
+                println!("       $ {}", command);
+            }
+        }
+        println!();
+    }
+
     // Attack graph visualization (ASCII)
     if visualize && total_events > 0 {
-        println!("  🗺️  Attack Path Visualization:");
+        println!("  {} Attack Path Visualization:", plain::text("🗺️ "));
         println!();
-        println!("       ┌─────────────────┐");
-        println!("       │ Initial Access  │");
-        println!("       └────────┬────────┘");
-        println!("                │");
-        println!("                ▼");
-        println!("       ┌─────────────────┐");
-        println!("       │   Execution     │");
-        println!("       └────────┬────────┘");
-        println!("                │");
-        println!("                ▼");
-        println!("       ┌─────────────────┐");
-        println!("       │  Persistence    │");
-        println!("       └────────┬────────┘");
-        println!("                │");
-        println!("                ▼");
-        println!("       ┌─────────────────┐");
-        println!("       │ Privilege Esc   │");
-        println!("       └────────┬────────┘");
-        println!("                │");
-        println!("                ▼");
-        println!("       ┌─────────────────┐");
-        println!("       │  Impact/Goals   │");
-        println!("       └─────────────────┘");
+        println!("       {}", plain::text("┌─────────────────┐"));
+        println!("       {} Initial Access  {}", plain::text("│"), plain::text("│"));
+        println!("       {}", plain::text("└────────┬────────┘"));
+        println!("                {}", plain::text("│"));
+        println!("                {}", plain::text("▼"));
+        println!("       {}", plain::text("┌─────────────────┐"));
+        println!("       {}   Execution     {}", plain::text("│"), plain::text("│"));
+        println!("       {}", plain::text("└────────┬────────┘"));
+        println!("                {}", plain::text("│"));
+        println!("                {}", plain::text("▼"));
+        println!("       {}", plain::text("┌─────────────────┐"));
+        println!("       {}  Persistence    {}", plain::text("│"), plain::text("│"));
+        println!("       {}", plain::text("└────────┬────────┘"));
+        println!("                {}", plain::text("│"));
+        println!("                {}", plain::text("▼"));
+        println!("       {}", plain::text("┌─────────────────┐"));
+        println!("       {} Privilege Esc   {}", plain::text("│"), plain::text("│"));
+        println!("       {}", plain::text("└────────┬────────┘"));
+        println!("                {}", plain::text("│"));
+        println!("                {}", plain::text("▼"));
+        println!("       {}", plain::text("┌─────────────────┐"));
+        println!("       {}  Impact/Goals   {}", plain::text("│"), plain::text("│"));
+        println!("       {}", plain::text("└─────────────────┘"));
         println!();
     }
 
@@ -9722,22 +11180,18 @@ pub fn evolve_command(
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
-        }
+        g.mount_os_ro(root).ok();
     }
 
     progress.set_message("Analyzing evolution path...");
@@ -9751,7 +11205,7 @@ pub fn evolve_command(
     println!();
 
     // Analyze current state
-    println!("📊 Current State Analysis:");
+    println!("{}Current State Analysis:", plain::text("📊 "));
     println!();
 
     let mut current_score = 0u32;
@@ -9839,7 +11293,7 @@ pub fn evolve_command(
     println!();
 
     // Evolution roadmap
-    println!("🚀 Evolution Roadmap:");
+    println!("{}Evolution Roadmap:", plain::text("🚀 "));
     println!();
 
     // Sort improvements by stage
@@ -9861,16 +11315,16 @@ pub fn evolve_command(
             println!();
 
             for (category, improvement, _, gain) in stage_improvements {
-                println!("    • [{}] {}", category, improvement);
+                println!("    {} [{}] {}", plain::text("•"), category, improvement);
                 println!("      Impact: +{} points", gain);
             }
             println!();
 
             if safety_checks {
                 println!("    Safety Checks:");
-                println!("      ✓ Pre-stage snapshot required");
-                println!("      ✓ Validation testing before next stage");
-                println!("      ✓ Rollback plan documented");
+                println!("      {} Pre-stage snapshot required", plain::text("✓"));
+                println!("      {} Validation testing before next stage", plain::text("✓"));
+                println!("      {} Rollback plan documented", plain::text("✓"));
                 println!();
             }
         }
@@ -9880,7 +11334,7 @@ pub fn evolve_command(
     let total_improvement: u32 = improvement_areas.iter().map(|(_, _, _, gain)| gain).sum();
     let projected_score = current_avg + total_improvement;
 
-    println!("📈 Projected Outcome:");
+    println!("{}Projected Outcome:", plain::text("📈 "));
     println!();
     println!("  Current:   {}/100", current_avg);
     println!("  Projected: {}/100", projected_score.min(100));
@@ -9897,7 +11351,7 @@ pub fn evolve_command(
     println!("  Evolution Risk: {} - {}", evolution_risk.0, evolution_risk.1);
     println!();
 
-    println!("⚙️  Implementation Guidelines:");
+    println!("{} Implementation Guidelines:", plain::text("⚙️ "));
     println!();
     println!("  1. Create snapshot before each stage");
     println!("  2. Apply changes in isolated environment first");
@@ -9967,22 +11421,18 @@ pub fn verify_command(
 
     let progress = ProgressReporter::spinner("Loading disk image...");
     g.add_drive_ro(image.to_str().unwrap())?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
 
     progress.set_message("Launching appliance...");
     g.launch()?;
 
     // Mount filesystems
     progress.set_message("Mounting filesystems...");
-    let roots = g.inspect_os().unwrap_or_default();
+    let roots = target::os_roots(&mut g)?;
     if !roots.is_empty() {
         let root = &roots[0];
-        if let Ok(mountpoints) = g.inspect_get_mountpoints(root) {
-            let mut mounts: Vec<_> = mountpoints.iter().collect();
-            mounts.sort_by_key(|(mount, _)| std::cmp::Reverse(mount.len()));
-            for (mount, device) in mounts {
-                g.mount_ro(device, mount).ok();
-            }
-        }
+        g.mount_os_ro(root).ok();
     }
 
     progress.set_message("Executing zero-trust verification...");
@@ -10001,7 +11451,7 @@ pub fn verify_command(
 
     // Identity Verification
     if check_identity {
-        println!("🔐 Identity Verification:");
+        println!("{}Identity Verification:", plain::text("🔐 "));
         println!();
 
         total_checks += 1;
@@ -10009,17 +11459,17 @@ pub fn verify_command(
             if let Ok(content) = g.read_file("/etc/machine-id") {
                 if let Ok(machine_id) = String::from_utf8(content) {
                     let id = machine_id.trim();
-                    println!("  ✓ System Identity: {}", id);
+                    println!("  {} System Identity: {}", plain::text("✓"), id);
                     verification_results.insert("machine-id", "VERIFIED");
                     passed_checks += 1;
                 } else {
-                    println!("  ❌ Machine ID corrupt");
+                    println!("  {} Machine ID corrupt", plain::text("❌"));
                     verification_results.insert("machine-id", "FAILED");
                     failed_checks += 1;
                 }
             }
         } else {
-            println!("  ⚠️  No machine ID found");
+            println!("  {}  No machine ID found", plain::text("⚠️"));
             verification_results.insert("machine-id", "MISSING");
             failed_checks += 1;
         }
@@ -10035,11 +11485,19 @@ pub fn verify_command(
                         .count();
 
                     if suspicious_users > 0 {
-                        println!("  ❌ Suspicious user accounts detected: {}", suspicious_users);
+                        println!(
+                            "  {} Suspicious user accounts detected: {}",
+                            plain::text("❌"),
+                            suspicious_users
+                        );
                         verification_results.insert("user-accounts", "FAILED");
                         failed_checks += 1;
                     } else {
-                        println!("  ✓ User accounts verified ({} users)", user_count);
+                        println!(
+                            "  {} User accounts verified ({} users)",
+                            plain::text("✓"),
+                            user_count
+                        );
                         verification_results.insert("user-accounts", "VERIFIED");
                         passed_checks += 1;
                     }
@@ -10052,7 +11510,7 @@ pub fn verify_command(
 
     // Integrity Verification
     if check_integrity {
-        println!("🔍 Integrity Verification:");
+        println!("{}Integrity Verification:", plain::text("🔍 "));
         println!();
 
         // Critical system files
@@ -10068,16 +11526,16 @@ pub fn verify_command(
             total_checks += 1;
             if g.is_file(file).unwrap_or(false) {
                 if let Ok(checksum) = g.checksum("sha256", file) {
-                    println!("  ✓ {}: SHA256:{}", file, &checksum[..16]);
+                    println!("  {} {}: SHA256:{}", plain::text("✓"), file, &checksum[..16]);
                     verification_results.insert(*file, "VERIFIED");
                     passed_checks += 1;
                 } else {
-                    println!("  ❌ {} checksum failed", file);
+                    println!("  {} {} checksum failed", plain::text("❌"), file);
                     verification_results.insert(*file, "FAILED");
                     failed_checks += 1;
                 }
             } else {
-                println!("  ⚠️  {} missing", file);
+                println!("  {}  {} missing", plain::text("⚠️"), file);
                 verification_results.insert(*file, "MISSING");
                 failed_checks += 1;
             }
@@ -10088,7 +11546,7 @@ pub fn verify_command(
 
     // Supply Chain Verification
     if check_supply_chain {
-        println!("📦 Supply Chain Verification:");
+        println!("{}Supply Chain Verification:", plain::text("📦 "));
         println!();
 
         total_checks += 1;
@@ -10102,11 +11560,15 @@ pub fn verify_command(
                 let unsigned_packages = apps.len() - signed_packages;
 
                 if unsigned_packages > 0 {
-                    println!("  ⚠️  {} unsigned packages detected", unsigned_packages);
+                    println!(
+                        "  {}  {} unsigned packages detected",
+                        plain::text("⚠️"),
+                        unsigned_packages
+                    );
                     verification_results.insert("package-signatures", "WARNING");
                     failed_checks += 1;
                 } else {
-                    println!("  ✓ All packages signed and verified");
+                    println!("  {} All packages signed and verified", plain::text("✓"));
                     verification_results.insert("package-signatures", "VERIFIED");
                     passed_checks += 1;
                 }
@@ -10115,11 +11577,11 @@ pub fn verify_command(
                 total_checks += 1;
                 if g.is_dir("/etc/apt/sources.list.d").unwrap_or(false)
                     || g.is_file("/etc/yum.repos.d").unwrap_or(false) {
-                    println!("  ✓ Repository configuration present");
+                    println!("  {} Repository configuration present", plain::text("✓"));
                     verification_results.insert("repo-trust", "VERIFIED");
                     passed_checks += 1;
                 } else {
-                    println!("  ⚠️  Repository configuration not found");
+                    println!("  {}  Repository configuration not found", plain::text("⚠️"));
                     verification_results.insert("repo-trust", "WARNING");
                     failed_checks += 1;
                 }
@@ -10128,7 +11590,10 @@ pub fn verify_command(
 
         // Software bill of materials (SBOM)
         total_checks += 1;
-        println!("  ℹ️  SBOM generation recommended for complete supply chain transparency");
+        println!(
+            "  {}  SBOM generation recommended for complete supply chain transparency",
+            plain::text("ℹ️")
+        );
         verification_results.insert("sbom", "RECOMMENDED");
 
         println!();
@@ -10162,25 +11627,28 @@ pub fn verify_command(
     };
 
     println!("  Trust Score: {}/100", trust_score);
-    println!("  Trust Level: {} {} - {}", trust_level.1, trust_level.0, trust_level.2);
+    println!("  Trust Level: {} {} - {}", plain::text(trust_level.1), trust_level.0, trust_level.2);
     println!();
 
     if failed_checks > 0 {
-        println!("  ⚠️  Zero-Trust Violations Detected:");
+        println!("  {}  Zero-Trust Violations Detected:", plain::text("⚠️"));
         println!();
         for (check, result) in &verification_results {
             if result == &"FAILED" || result == &"MISSING" {
-                println!("    • {} - {}", check, result);
+                println!("    {} {} - {}", plain::text("•"), check, result);
             }
         }
         println!();
         println!("  Recommendation: Quarantine system until issues are resolved");
     } else {
-        println!("  ✅ All verifications passed - system meets zero-trust requirements");
+        println!(
+            "  {} All verifications passed - system meets zero-trust requirements",
+            plain::text("✅")
+        );
     }
 
     println!();
-    println!("🔄 Continuous Verification:");
+    println!("{}Continuous Verification:", plain::text("🔄 "));
     println!();
     println!("  Zero-trust requires ongoing verification:");
     println!("  1. Re-verify on every access attempt");
@@ -10228,60 +11696,128 @@ pub fn inventory_command(
     include_licenses: bool,
     include_files: bool,
     include_cves: bool,
-    _severity: Option<String>,
+    severity: Option<String>,
+    vex: &[PathBuf],
     summary: bool,
+    container_sboms: bool,
+    sign_key: Option<&Path>,
     verbose: bool,
 ) -> Result<()> {
-    use crate::cli::inventory::{self, SbomFormat};
+    use crate::cli::inventory::{self, attest, SbomFormat};
 
     if verbose {
-        println!("📋 Generating SBOM for: {}", image.display());
+        println!("{}Generating SBOM for: {}", plain::text("📋 "), image.display());
     }
 
-    // Generate inventory
-    let inventory = inventory::generate_inventory(
+    // Parse format
+    let sbom_format = SbomFormat::from_str(format)?;
+    // The attestations are about the image, so hash it before inspecting
+    let digest = match sign_key {
+        Some(_) if attest::predicate_type(sbom_format).is_none() => {
+            anyhow::bail!("Only SPDX and CycloneDX JSON SBOMs can be signed, not {}", format);
+        }
+        Some(_) => Some(attest::image_digest(image)?),
+        None => None,
+    };
+
+    let vex = Vex::load(vex)?;
+
+    // Generate inventory, once per operating system
+    let mut inventories = inventory::generate_inventory(
         image,
         include_licenses,
         include_cves,
         include_files,
+        container_sboms,
     )?;
-
-    // Show summary if requested
-    if summary {
-        let summary_text = inventory::sbom::generate_summary(&inventory);
-        println!("{}", summary_text);
+    if !vex.is_empty() {
+        for inventory in &mut inventories {
+            let suppressed = inventory::apply_vex(inventory, &vex);
+            if verbose {
+                println!(
+                    "{}{} CVEs marked not affected or fixed by VEX",
+                    plain::text("🧹 "),
+                    suppressed
+                );
+            }
+        }
+    }
+    if let Some(min_severity) = &severity {
+        for inventory in &mut inventories {
+            inventory::retain_severity(inventory, min_severity);
+        }
     }
 
-    // Parse format
-    let sbom_format = SbomFormat::from_str(format)?;
+    for (index, inventory) in inventories.iter().enumerate() {
+        // Several OSes: number the output files like --os-root numbers roots
+        let output = match output {
+            Some(path) if inventories.len() > 1 => Some(numbered_path(path, index + 1)),
+            _ => output.map(str::to_string),
+        };
+        if inventories.len() > 1 {
+            eprintln!("{}OS root {}: {}", plain::text("💽 "), index + 1, inventory.root);
+        }
 
-    if verbose {
-        println!("📤 Exporting as {} format...", format);
-    }
+        // Show summary if requested
+        if summary {
+            let summary_text = inventory::sbom::generate_summary(inventory);
+            println!("{}", plain::text(&summary_text));
+        }
+
+        if verbose {
+            println!("{}Exporting as {} format...", plain::text("📤 "), format);
+        }
 
-    // Export inventory
-    inventory::export_inventory(&inventory, sbom_format, output)?;
+        // Export inventory
+        let content = inventory::export_inventory(inventory, sbom_format, output.as_deref())?;
 
-    if !summary && output.is_none() {
-        // If no summary shown and output to stdout, add a brief message
-        eprintln!("\n✅ SBOM generated successfully ({} packages)", inventory.statistics.total_packages);
+        // Signed in-toto attestation next to the SBOM
+        if let (Some(key), Some(digest), Some(path)) = (sign_key, &digest, &output) {
+            let name = image.file_name().unwrap_or_default().to_string_lossy();
+            let statement = attest::statement(&name, digest, sbom_format, &content)?;
+            let envelope = attest::sign(&statement, key)?;
+            let attestation = attest::attestation_path(path);
+            std::fs::write(&attestation, format!("{}\n", serde_json::to_string(&envelope)?))
+                .with_context(|| format!("Failed to write {}", attestation))?;
+            println!("{}Attestation written to: {}", plain::text("🔏 "), attestation);
+        }
+
+        if !summary && output.is_none() {
+            // If no summary shown and output to stdout, add a brief message
+            eprintln!("\n{} SBOM generated successfully ({} packages)", plain::text("✅"), inventory.statistics.total_packages);
+        }
     }
 
     Ok(())
 }
 
+/// `sbom.json` -> `sbom-2.json`
+fn numbered_path(path: &str, number: usize) -> String {
+    let path = Path::new(path);
+    let stem = path.file_stem().unwrap_or_default().to_string_lossy();
+    let name = match path.extension() {
+        Some(extension) => format!("{}-{}.{}", stem, number, extension.to_string_lossy()),
+        None => format!("{}-{}", stem, number),
+    };
+    path.with_file_name(name).to_string_lossy().into_owned()
+}
+
 /// Validate disk image against policy
+#[allow(clippy::too_many_arguments)]
 pub fn validate_command(
     image: &Path,
     policy_path: Option<&Path>,
     benchmark: Option<String>,
+    level: Option<u8>,
+    rego_path: Option<&Path>,
+    dump_facts: bool,
     example_policy: bool,
     format: &str,
     output: Option<&Path>,
     strict: bool,
     verbose: bool,
 ) -> Result<()> {
-    use crate::cli::validate::{self, Benchmark, Policy};
+    use crate::cli::validate::{self, Policy, RegoPolicy};
 
     // Generate example policy if requested
     if example_policy {
@@ -10290,59 +11826,116 @@ pub fn validate_command(
         
         if let Some(out_path) = output {
             std::fs::write(out_path, yaml)?;
-            println!("✅ Example policy written to: {}", out_path.display());
+            println!(
+                "{}",
+                i18n::tr_args(
+                    "written-example-policy",
+                    &[("path", out_path.display().to_string().into())]
+                )
+            );
         } else {
             println!("{}", yaml);
         }
         return Ok(());
     }
 
-    // Load or create policy
-    let policy = if let Some(path) = policy_path {
-        if verbose {
-            println!("📋 Loading policy from: {}", path.display());
+    // Image facts, as Rego policies get them for input
+    if dump_facts {
+        let facts = validate::image_facts(image, verbose)?;
+        let json = match facts.as_slice() {
+            [facts] => serde_json::to_string_pretty(facts)?,
+            _ => serde_json::to_string_pretty(&facts)?,
+        };
+        match output {
+            Some(out_path) => std::fs::write(out_path, json)?,
+            None => println!("{}", json),
         }
-        Policy::from_file(path)?
-    } else if let Some(bench) = benchmark {
+        return Ok(());
+    }
+
+    // Run validation, once per operating system
+    let reports = if let Some(path) = rego_path {
         if verbose {
-            println!("📋 Using benchmark: {}", bench);
+            println!("{}Loading Rego policy from: {}", plain::text("📋 "), path.display());
         }
-        let benchmark_type = Benchmark::from_str(&bench)
-            .ok_or_else(|| anyhow::anyhow!("Unknown benchmark: {}", bench))?;
-        benchmark_type.to_policy()
+        let policy = RegoPolicy::load(path)?;
+        validate::validate_image_rego(image, &policy, verbose)?
     } else {
-        // Use example policy as default
-        if verbose {
-            println!("📋 Using example policy");
+        let mut policy = load_policy(policy_path, benchmark, verbose)?;
+        if let Some(level) = level {
+            policy = policy.at_level(level);
         }
-        Policy::example()
+        validate::validate_image(image, &policy, verbose)?
     };
 
-    // Run validation
-    let report = validate::validate_image(image, &policy, verbose)?;
-
-    // Format output
-    let output_text = match format {
-        "json" => serde_json::to_string_pretty(&report)?,
-        _ => validate::format_report(&report),
+    // Format output; JSON is a single report unless the image has several OSes
+    let output_text = match (format, reports.as_slice()) {
+        ("json", [report]) => serde_json::to_string_pretty(report)?,
+        ("json", _) => serde_json::to_string_pretty(&reports)?,
+        _ => plain::report(
+            reports
+                .iter()
+                .map(validate::format_report)
+                .collect::<Vec<_>>()
+                .join("\n"),
+            output.is_none(),
+        ),
     };
 
     // Write or print output
     if let Some(out_path) = output {
         std::fs::write(out_path, output_text)?;
-        println!("✅ Validation report written to: {}", out_path.display());
+        println!(
+            "{}",
+            i18n::tr_args(
+                "written-validation-report",
+                &[("path", out_path.display().to_string().into())]
+            )
+        );
     } else {
         println!("{}", output_text);
     }
 
     // Exit with error if strict mode and failures found
-    if strict && report.summary.failed > 0 {
+    if strict && reports.iter().any(|report| report.summary.failed > 0) {
         std::process::exit(1);
     }
 
     Ok(())
 }
 
+/// The policy file, benchmark or example policy to validate against
+fn load_policy(
+    policy_path: Option<&Path>,
+    benchmark: Option<String>,
+    verbose: bool,
+) -> Result<crate::cli::validate::Policy> {
+    use crate::cli::validate::{Benchmark, Policy};
+
+    // Load or create policy
+    let policy = if let Some(path) = policy_path {
+        if verbose {
+            println!("{}Loading policy from: {}", plain::text("📋 "), path.display());
+        }
+        Policy::from_file(path)?
+    } else if let Some(bench) = benchmark {
+        if verbose {
+            println!("{}Using benchmark: {}", plain::text("📋 "), bench);
+        }
+        let benchmark_type = Benchmark::from_str(&bench)
+            .ok_or_else(|| anyhow::anyhow!("Unknown benchmark: {}", bench))?;
+        benchmark_type.to_policy()
+    } else {
+        // Use example policy as default
+        if verbose {
+            println!("{}Using example policy", plain::text("📋 "));
+        }
+        Policy::example()
+    };
+
+    Ok(policy)
+}
+
 /// License compliance checking
 pub fn license_command(
     image: &Path,
@@ -10365,7 +11958,13 @@ pub fn license_command(
         
         if let Some(out_path) = output {
             std::fs::write(out_path, notices)?;
-            println!("✅ Attribution notices written to: {}", out_path.display());
+            println!(
+                "{}",
+                i18n::tr_args(
+                    "written-attribution",
+                    &[("path", out_path.display().to_string().into())]
+                )
+            );
         } else {
             println!("{}", notices);
         }
@@ -10376,20 +11975,26 @@ pub fn license_command(
     let output_text = match format {
         "json" => serde_json::to_string_pretty(&report)?,
         "csv" => license::reporter::format_csv(&report),
-        _ => license::reporter::format_report(&report, details),
+        _ => plain::report(license::reporter::format_report(&report, details), output.is_none()),
     };
 
     // Write or print output
     if let Some(out_path) = output {
         std::fs::write(out_path, output_text)?;
-        println!("✅ License report written to: {}", out_path.display());
+        println!(
+            "{}",
+            i18n::tr_args(
+                "written-license-report",
+                &[("path", out_path.display().to_string().into())]
+            )
+        );
     } else {
         println!("{}", output_text);
     }
 
     // Exit with error if strict mode and violations found
     if strict && !report.violations.is_empty() {
-        eprintln!("❌ License compliance check failed: {} violations found", report.violations.len());
+        eprintln!("{} License compliance check failed: {} violations found", plain::text("❌"), report.violations.len());
         std::process::exit(1);
     }
 
@@ -10411,14 +12016,14 @@ pub fn blueprint_command(
         .ok_or_else(|| anyhow::anyhow!("Invalid format: {}. Must be terraform, ansible, kubernetes, or compose", format))?;
 
     if verbose {
-        println!("🔍 Analyzing image: {}", image.display());
+        println!("{}Analyzing image: {}", plain::text("🔍 "), image.display());
     }
 
     // Analyze image
     let analysis = blueprint::analyze_image(image, verbose)?;
 
     if verbose {
-        println!("✅ Analysis complete");
+        println!("{} Analysis complete", plain::text("✅"));
         println!("  OS: {} {}", analysis.os_name, analysis.os_version);
         println!("  Hostname: {}", analysis.hostname);
         println!("  Packages: {}", analysis.packages.len());
@@ -10430,7 +12035,7 @@ pub fn blueprint_command(
 
     // Generate blueprint
     if verbose {
-        println!("📝 Generating {} blueprint...", format);
+        println!("{}Generating {} blueprint...", plain::text("📝 "), format);
     }
 
     let blueprint_text = blueprint::generate_blueprint(&analysis, blueprint_format, provider)?;
@@ -10438,7 +12043,13 @@ pub fn blueprint_command(
     // Write or print output
     if let Some(out_path) = output {
         std::fs::write(out_path, &blueprint_text)?;
-        println!("✅ Blueprint written to: {}", out_path.display());
+        println!(
+            "{}",
+            i18n::tr_args(
+                "written-blueprint",
+                &[("path", out_path.display().to_string().into())]
+            )
+        );
     } else {
         println!("{}", blueprint_text);
     }
@@ -10466,19 +12077,19 @@ pub fn migrate_command(
         ))?;
 
     if verbose {
-        println!("🔍 Analyzing source system: {}", image.display());
+        println!("{}Analyzing source system: {}", plain::text("🔍 "), image.display());
     }
 
     // Analyze source system
     let source = migrate::analyze_source(image, verbose)?;
 
     if verbose {
-        println!("✅ Analysis complete");
+        println!("{} Analysis complete", plain::text("✅"));
         println!("  OS: {} {}", source.os_name, source.os_version);
         println!("  Packages: {}", source.packages.len());
         println!("  Services: {}", source.services.len());
         println!();
-        println!("📋 Planning migration to {}...", target);
+        println!("{}Planning migration to {}...", plain::text("📋 "), target);
     }
 
     // Plan migration
@@ -10486,7 +12097,7 @@ pub fn migrate_command(
     let plan = migrate::plan_migration(&source, target, target_version, migration_type)?;
 
     if verbose {
-        println!("✅ Migration plan generated");
+        println!("{} Migration plan generated", plain::text("✅"));
         println!("  Overall Risk: {:?}", plan.overall_risk);
         println!("  Compatibility Score: {:.1}%", plan.compatibility_score);
         println!("  Issues: {}", plan.issues.len());
@@ -10497,13 +12108,19 @@ pub fn migrate_command(
     let output_text = match format {
         "json" => serde_json::to_string_pretty(&plan)?,
         "html" => migrate::reporter::format_html(&plan),
-        _ => migrate::reporter::format_report(&plan, detailed),
+        _ => plain::report(migrate::reporter::format_report(&plan, detailed), output.is_none()),
     };
 
     // Write or print output
     if let Some(out_path) = output {
         std::fs::write(out_path, &output_text)?;
-        println!("✅ Migration plan written to: {}", out_path.display());
+        println!(
+            "{}",
+            i18n::tr_args(
+                "written-migration-plan",
+                &[("path", out_path.display().to_string().into())]
+            )
+        );
     } else {
         println!("{}", output_text);
     }
@@ -10530,7 +12147,7 @@ pub fn cost_command(
         ))?;
 
     if verbose {
-        println!("💰 Analyzing costs for: {}", image.display());
+        println!("{}Analyzing costs for: {}", plain::text("💰 "), image.display());
         println!("   Provider: {}", provider.as_str());
         println!("   Region: {}", region);
     }
@@ -10539,7 +12156,7 @@ pub fn cost_command(
     let analysis = cost::analyze_costs(image, provider, region, verbose)?;
 
     if verbose {
-        println!("✅ Cost analysis complete");
+        println!("{} Cost analysis complete", plain::text("✅"));
         println!("   Current: ${:.2}/month", analysis.current_estimate.total_monthly);
         println!("   Optimized: ${:.2}/month", analysis.optimized_estimate.total_monthly);
         println!("   Savings: ${:.2}/month ({:.1}%)", 
@@ -10552,13 +12169,19 @@ pub fn cost_command(
     let output_text = match format {
         "json" => serde_json::to_string_pretty(&analysis)?,
         "csv" => cost::reporter::format_csv(&analysis),
-        _ => cost::reporter::format_report(&analysis, detailed),
+        _ => plain::report(cost::reporter::format_report(&analysis, detailed), output.is_none()),
     };
 
     // Write or print output
     if let Some(out_path) = output {
         std::fs::write(out_path, &output_text)?;
-        println!("✅ Cost analysis written to: {}", out_path.display());
+        println!(
+            "{}",
+            i18n::tr_args(
+                "written-cost-analysis",
+                &[("path", out_path.display().to_string().into())]
+            )
+        );
     } else {
         println!("{}", output_text);
     }
@@ -10581,14 +12204,14 @@ pub fn dependencies_command(
     use crate::cli::dependencies;
 
     if verbose {
-        println!("🔍 Analyzing dependencies: {}", image.display());
+        println!("{}Analyzing dependencies: {}", plain::text("🔍 "), image.display());
     }
 
     // Analyze dependency graph
     let graph = dependencies::analyze_dependencies(image, verbose)?;
 
     if verbose {
-        println!("✅ Dependency analysis complete");
+        println!("{} Dependency analysis complete", plain::text("✅"));
         println!("   Packages: {}", graph.statistics.total_packages);
         println!("   Dependencies: {}", graph.statistics.total_dependencies);
         println!("   Circular: {}", graph.statistics.circular_dependencies);
@@ -10609,19 +12232,28 @@ pub fn dependencies_command(
             "json" => dependencies::graph::export_json(&graph)?,
             "csv" => dependencies::graph::export_csv(&graph),
             "html" => dependencies::graph::export_html(&graph),
-            _ => dependencies::visualizer::format_report(&graph, detailed),
+            _ => plain::report(
+                dependencies::visualizer::format_report(&graph, detailed),
+                output.is_none(),
+            ),
         }
     };
 
     // Write or print output
     if let Some(out_path) = output {
         std::fs::write(out_path, &output_text)?;
-        println!("✅ Dependency graph written to: {}", out_path.display());
+        println!(
+            "{}",
+            i18n::tr_args(
+                "written-dependency-graph",
+                &[("path", out_path.display().to_string().into())]
+            )
+        );
 
         // Print helpful message based on format
         match format {
-            "dot" => println!("💡 Generate visualization: dot -Tpng {} -o graph.png", out_path.display()),
-            "html" => println!("💡 Open in browser: open {}", out_path.display()),
+            "dot" => println!("{}Generate visualization: dot -Tpng {} -o graph.png", plain::text("💡 "), out_path.display()),
+            "html" => println!("{}Open in browser: open {}", plain::text("💡 "), out_path.display()),
             _ => {}
         }
     } else {
@@ -10630,3 +12262,543 @@ pub fn dependencies_command(
 
     Ok(())
 }
+
+/// Inventory setuid/setgid and world-writable files against distro baselines
+pub fn permissions_command(
+    image: &Path,
+    format: &str,
+    output: Option<&Path>,
+    baseline: Option<&Path>,
+    show_all: bool,
+    strict: bool,
+    verbose: bool,
+) -> Result<()> {
+    use crate::cli::permissions;
+
+    // Scan special permissions
+    let report = permissions::scan_permissions(image, baseline, verbose)?;
+
+    // Format output
+    let output_text = match format {
+        "json" => serde_json::to_string_pretty(&report)?,
+        "csv" => permissions::reporter::format_csv(&report),
+        _ => plain::report(
+            permissions::reporter::format_report(&report, show_all),
+            output.is_none(),
+        ),
+    };
+
+    // Write or print output
+    if let Some(out_path) = output {
+        std::fs::write(out_path, output_text)?;
+        println!(
+            "{}",
+            i18n::tr_args(
+                "written-permissions-report",
+                &[("path", out_path.display().to_string().into())]
+            )
+        );
+    } else {
+        println!("{}", output_text);
+    }
+
+    // Exit with error if strict mode and anomalies found
+    let findings = report.summary.anomalies + report.summary.orphaned;
+    if strict && findings > 0 {
+        eprintln!(
+            "{} Permission check failed: {} entries deviate from the baseline",
+            plain::text("❌"),
+            findings
+        );
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Collect kernel crash dumps, coredumps and core files from the guest
+pub fn crashes_command(
+    image: &Path,
+    format: &str,
+    output: Option<&Path>,
+    extract: Option<&Path>,
+    verbose: bool,
+) -> Result<()> {
+    use crate::cli::crash;
+
+    // Collect crash evidence
+    let report = crash::scan_crashes(image, extract, verbose)?;
+
+    // Format output
+    let output_text = match format {
+        "json" => serde_json::to_string_pretty(&report)?,
+        "csv" => crash::reporter::format_csv(&report),
+        _ => plain::report(crash::reporter::format_report(&report), output.is_none()),
+    };
+
+    // Write or print output
+    if let Some(out_path) = output {
+        std::fs::write(out_path, output_text)?;
+        println!(
+            "{}",
+            i18n::tr_args(
+                "written-crash-report",
+                &[("path", out_path.display().to_string().into())]
+            )
+        );
+    } else {
+        println!("{}", output_text);
+    }
+
+    if let Some(dir) = extract {
+        println!(
+            "{} {} crash artifacts extracted to: {}",
+            plain::text("✅"),
+            report.extracted.len(),
+            dir.display()
+        );
+    }
+
+    Ok(())
+}
+
+/// Check guest configuration files for syntax errors
+pub fn lint_command(
+    image: &Path,
+    paths: &[String],
+    kind: Option<&str>,
+    format: &str,
+    output: Option<&Path>,
+    strict: bool,
+    verbose: bool,
+) -> Result<()> {
+    use crate::cli::lint;
+    use guestkit::lint::FileKind;
+
+    let kind = match kind {
+        Some(name) => Some(FileKind::from_name(name).ok_or_else(|| {
+            let kinds: Vec<&str> = FileKind::ALL.iter().map(|k| k.as_str()).collect();
+            anyhow::anyhow!("Unknown file kind '{}' (expected one of: {})", name, kinds.join(", "))
+        })?),
+        None => None,
+    };
+
+    // Lint files
+    let report = lint::scan_lint(image, paths, kind, verbose)?;
+
+    // Format output
+    let output_text = match format {
+        "json" => serde_json::to_string_pretty(&report)?,
+        "csv" => lint::reporter::format_csv(&report),
+        _ => plain::report(lint::reporter::format_report(&report), output.is_none()),
+    };
+
+    // Write or print output
+    if let Some(out_path) = output {
+        std::fs::write(out_path, output_text)?;
+        println!(
+            "{}",
+            i18n::tr_args(
+                "written-lint-report",
+                &[("path", out_path.display().to_string().into())]
+            )
+        );
+    } else {
+        println!("{}", output_text);
+    }
+
+    // Exit with error if errors (or warnings in strict mode) were found
+    let findings = report.summary.errors + if strict { report.summary.warnings } else { 0 };
+    if findings > 0 {
+        eprintln!("{} Lint failed: {} issues found", plain::text("❌"), findings);
+        std::process::exit(1);
+    }
+
+    Ok(())
+}
+
+/// Collect a support bundle (logs, configs, package history) from a disk image
+pub fn support_bundle_command(
+    image: &Path,
+    output: Option<&Path>,
+    format: &str,
+    max_lines: usize,
+    redact: &[String],
+    no_redact: bool,
+    verbose: bool,
+) -> Result<()> {
+    use crate::cli::support_bundle::{self, redact::Redactor, BundleOptions};
+
+    if no_redact && !redact.is_empty() {
+        anyhow::bail!("--redact cannot be combined with --no-redact");
+    }
+
+    let redactor = if no_redact {
+        None
+    } else {
+        let mut redactor = Redactor::new();
+        for pattern in redact {
+            redactor = redactor.with_pattern(pattern)?;
+        }
+        Some(redactor)
+    };
+
+    let archive = output
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| support_bundle::default_archive_path(image));
+
+    let options = BundleOptions {
+        max_lines,
+        redactor,
+    };
+
+    // Collect and compress
+    let index = support_bundle::create_bundle(image, &archive, &options, verbose)?;
+
+    // Print index
+    let output_text = match format {
+        "json" => serde_json::to_string_pretty(&index)?,
+        _ => plain::report(support_bundle::reporter::format_report(&index), true),
+    };
+    println!("{}", output_text);
+
+    println!(
+        "{}",
+        i18n::tr_args(
+            "written-support-bundle",
+            &[("path", archive.display().to_string().into())]
+        )
+    );
+
+    Ok(())
+}
+
+/// Collect a hashed forensic triage package
+pub fn collect_command(
+    image: &Path,
+    profile: &str,
+    targets: &[String],
+    output: Option<&Path>,
+    format: &str,
+    verbose: bool,
+) -> Result<()> {
+    use crate::cli::collect::{self, targets as collect_targets, Profile};
+
+    let Some(profile) = Profile::from_name(profile) else {
+        anyhow::bail!("Unknown collection profile: {} (expected forensic)", profile);
+    };
+
+    // --target narrows the profile to the named targets
+    let selected = if targets.is_empty() {
+        profile.targets()
+    } else {
+        let mut selected = Vec::new();
+        for name in targets {
+            let Some(target) = collect_targets::target(name) else {
+                let known: Vec<&str> = collect_targets::TARGETS.iter().map(|t| t.name).collect();
+                anyhow::bail!("Unknown target: {} (expected one of: {})", name, known.join(", "));
+            };
+            selected.push(target);
+        }
+        selected
+    };
+
+    let archive = output
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| collect::default_archive_path(image));
+
+    // Collect, hash and compress
+    let manifest = collect::create_package(image, &archive, profile, &selected, verbose)?;
+
+    // Print manifest
+    let output_text = match format {
+        "json" => serde_json::to_string_pretty(&manifest)?,
+        _ => plain::report(collect::reporter::format_report(&manifest), true),
+    };
+    println!("{}", output_text);
+
+    println!(
+        "{}",
+        i18n::tr_args(
+            "written-triage-package",
+            &[("path", archive.display().to_string().into())]
+        )
+    );
+
+    Ok(())
+}
+
+/// Recover deleted files of an image into a directory
+pub fn recover_command(
+    image: &Path,
+    output: &Path,
+    paths: &[String],
+    format: &str,
+    verbose: bool,
+) -> Result<()> {
+    use crate::cli::recover;
+
+    let report = recover::recover(image, output, paths, verbose)?;
+    std::fs::write(
+        output.join(recover::REPORT_NAME),
+        serde_json::to_string_pretty(&report)?,
+    )?;
+
+    // Print report
+    let output_text = match format {
+        "json" => serde_json::to_string_pretty(&report)?,
+        _ => plain::report(recover::format_report(&report), true),
+    };
+    println!("{}", output_text);
+
+    println!(
+        "{}",
+        i18n::tr_args(
+            "written-recovered-files",
+            &[
+                ("files", report.recovered.len().into()),
+                ("path", output.display().to_string().into())
+            ]
+        )
+    );
+
+    Ok(())
+}
+
+/// Aggregate batch run results into a fleet dashboard
+pub fn fleet_report_command(
+    results: &Path,
+    history: &[PathBuf],
+    format: &str,
+    output: Option<&Path>,
+    verbose: bool,
+) -> Result<()> {
+    use crate::cli::fleet;
+
+    let history: Vec<&Path> = history.iter().map(PathBuf::as_path).collect();
+    // Results for cataloged images are merged under their catalog id
+    let catalog = match crate::cli::catalog::Catalog::load_default() {
+        Ok(catalog) => Some(catalog),
+        Err(e) => {
+            log::warn!("Ignoring image catalog: {:#}", e);
+            None
+        }
+    };
+    let report = fleet::build_report(results, &history, catalog.as_ref())?;
+
+    // Format output
+    let output_text = match format {
+        "json" => serde_json::to_string_pretty(&report)?,
+        "csv" => fleet::reporter::format_csv(&report),
+        "text" => plain::report(fleet::reporter::format_report(&report, verbose), output.is_none()),
+        _ => fleet::reporter::format_html(&report),
+    };
+
+    // Write or print output
+    if let Some(out_path) = output {
+        std::fs::write(out_path, output_text)?;
+        println!(
+            "{}",
+            i18n::tr_args(
+                "written-fleet-report",
+                &[
+                    ("images", report.current.summary.images.into()),
+                    ("path", out_path.display().to_string().into())
+                ]
+            )
+        );
+    } else {
+        println!("{}", output_text);
+    }
+
+    Ok(())
+}
+
+/// Report package manager history and automatic update configuration
+pub fn package_history_command(
+    image: &Path,
+    format: &str,
+    output: Option<&Path>,
+    days: Option<u32>,
+    package: Option<&str>,
+    verbose: bool,
+) -> Result<()> {
+    use crate::cli::package_history;
+
+    // Collect package history
+    let report = package_history::scan_package_history(image, days, package, verbose)?;
+
+    // Format output
+    let output_text = match format {
+        "json" => serde_json::to_string_pretty(&report)?,
+        "csv" => package_history::reporter::format_csv(&report),
+        _ => plain::report(package_history::reporter::format_report(&report), output.is_none()),
+    };
+
+    // Write or print output
+    if let Some(out_path) = output {
+        std::fs::write(out_path, output_text)?;
+        println!(
+            "{}",
+            i18n::tr_args(
+                "written-package-history",
+                &[("path", out_path.display().to_string().into())]
+            )
+        );
+    } else {
+        println!("{}", output_text);
+    }
+
+    Ok(())
+}
+
+/// Compare an image against its distro's published package manifests
+pub fn pristine_command(
+    image: &Path,
+    mirror: Option<String>,
+    manifests: Vec<String>,
+    verify_files: bool,
+    format: &str,
+    output: Option<&Path>,
+    verbose: bool,
+) -> Result<()> {
+    use crate::cli::pristine;
+
+    let options = pristine::PristineOptions {
+        mirror,
+        manifests,
+        verify_files,
+        verbose,
+    };
+
+    // Compare against upstream manifests
+    let report = pristine::scan_pristine(image, &options)?;
+
+    // Format output
+    let output_text = match format {
+        "json" => serde_json::to_string_pretty(&report)?,
+        "csv" => pristine::reporter::format_csv(&report),
+        _ => plain::report(pristine::reporter::format_report(&report), output.is_none()),
+    };
+
+    // Write or print output
+    if let Some(out_path) = output {
+        std::fs::write(out_path, output_text)?;
+        println!(
+            "{}",
+            i18n::tr_args(
+                "written-pristine-report",
+                &[("path", out_path.display().to_string().into())]
+            )
+        );
+    } else {
+        println!("{}", output_text);
+    }
+
+    Ok(())
+}
+
+/// Options for [`provenance_command`]
+#[derive(Debug, Clone, Default)]
+pub struct ProvenanceCommandOptions {
+    /// Image to compare against instead of the backing file
+    pub base: Option<PathBuf>,
+    /// Baseline recorded earlier to compare against
+    pub baseline: Option<PathBuf>,
+    /// Record a baseline to this file instead of comparing
+    pub record: Option<PathBuf>,
+    /// Guest directories to compare (`/` if empty)
+    pub paths: Vec<String>,
+}
+
+/// Report what changed since the base image
+pub fn provenance_command(
+    image: &Path,
+    options: ProvenanceCommandOptions,
+    format: &str,
+    output: Option<&Path>,
+    verbose: bool,
+) -> Result<()> {
+    use crate::cli::provenance;
+
+    let ProvenanceCommandOptions {
+        base,
+        baseline,
+        record,
+        paths,
+    } = options;
+    let options = provenance::ProvenanceOptions { paths, verbose };
+
+    // Record a baseline for later comparisons
+    if let Some(record_path) = record {
+        let baseline = provenance::record_baseline(image, &options)?;
+        std::fs::write(&record_path, serde_json::to_string_pretty(&baseline)?)?;
+        println!(
+            "{}",
+            i18n::tr_args(
+                "written-provenance-baseline",
+                &[
+                    ("path", record_path.display().to_string().into()),
+                    ("files", baseline.snapshot.files.len().into()),
+                ]
+            )
+        );
+        return Ok(());
+    }
+
+    let base = match (base, baseline) {
+        (Some(path), _) => provenance::Base::Image(path.display().to_string()),
+        (None, Some(path)) => provenance::Base::Baseline(path.display().to_string()),
+        (None, None) => provenance::Base::BackingFile,
+    };
+
+    // Compare against the base
+    let report = provenance::scan_provenance(image, &base, &options)?;
+
+    // Format output
+    let output_text = match format {
+        "json" => serde_json::to_string_pretty(&report)?,
+        "csv" => provenance::reporter::format_csv(&report),
+        "html" => provenance::reporter::format_html(&report),
+        _ => plain::report(provenance::reporter::format_report(&report, verbose), output.is_none()),
+    };
+
+    // Write or print output
+    if let Some(out_path) = output {
+        std::fs::write(out_path, output_text)?;
+        println!(
+            "{}",
+            i18n::tr_args(
+                "written-provenance-report",
+                &[("path", out_path.display().to_string().into())]
+            )
+        );
+    } else {
+        println!("{}", output_text);
+    }
+
+    Ok(())
+}
+
+/// Check host prerequisites and suggest fixes
+pub fn doctor_command(format: &str, verbose: bool) -> Result<()> {
+    use crate::cli::doctor;
+
+    let report = doctor::run_doctor();
+
+    // Format output
+    let output_text = match format {
+        "json" => serde_json::to_string_pretty(&report)?,
+        _ => plain::report(doctor::reporter::format_report(&report, verbose), true),
+    };
+    println!("{}", output_text);
+
+    if report.failures > 0 {
+        anyhow::bail!(
+            "{}",
+            i18n::tr_args("doctor-failed", &[("failures", report.failures.into())])
+        );
+    }
+
+    Ok(())
+}
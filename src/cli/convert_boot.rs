@@ -0,0 +1,284 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Convert-boot command - BIOS/MBR to UEFI/GPT boot conversion and back
+
+use crate::cli::align::Attached;
+use crate::cli::audit_log::audited;
+use crate::cli::catalog::parse_image_ref;
+use crate::cli::output::format_size;
+use crate::cli::plain;
+use crate::cli::target;
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use colored::*;
+use guestkit::disk::boot_mode::BootConversionPlan;
+use guestkit::disk::{BootLayout, BootMode, DiskReader, LoopDevice};
+use guestkit::guestfs::BootRepairReport;
+use guestkit::Guestfs;
+use serde_json::json;
+use std::os::unix::fs::FileTypeExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+use std::time::Duration;
+
+#[derive(Debug, Args)]
+pub struct ConvertBootCommand {
+    /// Disk image or block device
+    #[arg(value_parser = parse_image_ref)]
+    pub image: PathBuf,
+
+    /// Boot mode to convert to (bios, uefi)
+    #[arg(long, value_parser = parse_boot_mode)]
+    pub to: BootMode,
+
+    /// Show the conversion plan without changing the image
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Output format (text, json)
+    #[arg(short, long, default_value = "text")]
+    pub format: String,
+
+    /// Enable verbose output
+    #[arg(short, long)]
+    pub verbose: bool,
+}
+
+fn parse_boot_mode(value: &str) -> std::result::Result<BootMode, String> {
+    value
+        .parse()
+        .map_err(|e: guestkit::core::Error| e.to_string())
+}
+
+impl ConvertBootCommand {
+    pub fn execute(&self) -> Result<()> {
+        let block_device = std::fs::metadata(&self.image)
+            .map(|metadata| metadata.file_type().is_block_device())
+            .unwrap_or(false);
+        let layout = self
+            .analyze(block_device)
+            .with_context(|| format!("Failed to analyze {}", self.image.display()))?;
+        let plan = layout.conversion_plan(self.to)?;
+        if plan.grow > 0 && block_device {
+            bail!(
+                "{} needs {} more at the end for the ESP; grow the device first",
+                self.image.display(),
+                format_size(plan.grow)
+            );
+        }
+
+        if self.format == "json" && self.dry_run {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&json!({ "layout": layout, "plan": plan }))?
+            );
+            return Ok(());
+        }
+        if self.format != "json" {
+            print_plan(&self.image, &layout, &plan);
+        }
+        if self.dry_run {
+            return Ok(());
+        }
+
+        let parameters = json!({ "to": self.to, "plan": plan });
+        let report = audited("convert-boot", &self.image, parameters, || {
+            self.convert(&plan)
+        })?;
+
+        if self.format == "json" {
+            println!(
+                "{}",
+                serde_json::to_string_pretty(&json!({ "plan": plan, "report": report }))?
+            );
+        } else {
+            print_report(&report);
+            eprintln!(
+                "{} {} now boots with {}",
+                plain::text("✓").green(),
+                self.image.display(),
+                self.to.to_string().to_uppercase()
+            );
+        }
+        Ok(())
+    }
+
+    /// Raw images and block devices are read directly, others through NBD
+    fn analyze(&self, block_device: bool) -> Result<BootLayout> {
+        if block_device || LoopDevice::is_format_supported(&self.image) {
+            return Ok(BootLayout::analyze(&mut DiskReader::open(&self.image)?)?);
+        }
+
+        let attached = Attached::attach(&self.image, true)?;
+        let mut reader = DiskReader::open(attached.device_path()?)?;
+        Ok(BootLayout::analyze(&mut reader)?)
+    }
+
+    fn convert(&self, plan: &BootConversionPlan) -> Result<BootRepairReport> {
+        if plan.grow > 0 {
+            eprintln!("Growing the image by {}...", format_size(plan.grow));
+            run(Command::new("qemu-img")
+                .arg("resize")
+                .arg(&self.image)
+                .arg(format!("+{}", plan.grow)))
+            .context("Failed to grow the image. Is qemu-img installed?")?;
+        }
+
+        {
+            let attached = Attached::attach(&self.image, false)?;
+            let device = attached.device_path()?;
+            repartition(device, plan)?;
+
+            if let Some(esp) = &plan.new_esp {
+                let partition = attached.partition_path(esp.number)?;
+                wait_for(&partition)?;
+                eprintln!("Formatting the ESP...");
+                run(Command::new("mkfs.vfat")
+                    .arg("-F")
+                    .arg("32")
+                    .arg("-n")
+                    .arg("ESP")
+                    .arg(&partition))
+                .context("Failed to format the ESP. Is dosfstools installed?")?;
+            }
+        }
+
+        eprintln!("Installing the bootloader...");
+        let mut g = Guestfs::new()?;
+        g.set_verbose(self.verbose);
+        g.add_drive(self.image.to_str().context("Image path is not UTF-8")?)?;
+        target::add_disks(&mut g, false)?;
+        target::select(&mut g);
+        g.launch()?;
+        let roots = g.inspect_os()?;
+        let root = roots.first().context("No operating system found")?;
+        g.mount_os_rw(root)?;
+
+        let report = match &plan.new_esp {
+            Some(esp) => g.install_uefi_boot(root, &format!("/dev/sda{}", esp.number)),
+            None => g.install_bios_boot(root),
+        };
+        g.umount_all().ok();
+        g.shutdown().ok();
+        Ok(report?)
+    }
+}
+
+/// Change the partition table of `device` as the plan says
+fn repartition(device: &Path, plan: &BootConversionPlan) -> Result<()> {
+    if plan.from_table == "msdos" && plan.to_table == "gpt" {
+        eprintln!("Converting the partition table to GPT...");
+        run(Command::new("sgdisk").arg("--mbrtogpt").arg(device))
+            .context("Failed to convert to GPT. Is gdisk installed?")?;
+    } else if plan.from_table == "gpt" && plan.grow > 0 {
+        // The backup GPT goes to the new end of the disk
+        run(Command::new("sgdisk")
+            .arg("--move-second-header")
+            .arg(device))
+        .context("Failed to move the backup GPT. Is gdisk installed?")?;
+    }
+
+    if let Some(esp) = &plan.new_esp {
+        eprintln!("Creating the ESP ({})...", format_size(esp.size));
+        let first = esp.start / 512;
+        let last = first + esp.size / 512 - 1;
+        run(Command::new("sgdisk")
+            .arg(format!("--new={}:{}:{}", esp.number, first, last))
+            .arg(format!("--typecode={}:EF00", esp.number))
+            .arg(format!("--change-name={}:EFI System Partition", esp.number))
+            .arg(device))
+        .context("Failed to create the ESP. Is gdisk installed?")?;
+    }
+
+    if plan.to_table == "msdos" && !plan.mbr_partitions.is_empty() {
+        eprintln!("Converting the partition table to MBR...");
+        let numbers: Vec<String> = plan.mbr_partitions.iter().map(u32::to_string).collect();
+        run(Command::new("sgdisk")
+            .arg(format!("--gpttombr={}", numbers.join(":")))
+            .arg(device))
+        .context("Failed to convert to MBR. Is gdisk installed?")?;
+    }
+    if let Some(active) = plan.active {
+        run(Command::new("sfdisk")
+            .arg("--activate")
+            .arg(device)
+            .arg(active.to_string()))
+        .context("Failed to mark the boot partition active. Is util-linux installed?")?;
+    }
+
+    // Have the kernel pick up the new partitions
+    run(Command::new("blockdev").arg("--rereadpt").arg(device))?;
+    Command::new("udevadm").arg("settle").status().ok();
+    Ok(())
+}
+
+/// Wait for udev to create a partition device node
+fn wait_for(partition: &Path) -> Result<()> {
+    for _ in 0..50 {
+        if partition.exists() {
+            return Ok(());
+        }
+        std::thread::sleep(Duration::from_millis(100));
+    }
+    bail!("{} did not appear", partition.display())
+}
+
+fn run(command: &mut Command) -> Result<()> {
+    let output = command.output()?;
+    if !output.status.success() {
+        bail!(
+            "{} failed: {}",
+            command.get_program().to_string_lossy(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+fn print_plan(image: &Path, layout: &BootLayout, plan: &BootConversionPlan) {
+    println!("{}", image.display().to_string().bold());
+    println!("  Table:    {}", layout.table_type);
+    println!("  Boot:     {}", layout.mode().to_string().to_uppercase());
+    println!("  Size:     {}", format_size(layout.disk_size));
+    println!();
+
+    println!(
+        "{}",
+        format!("Conversion to {}:", plan.target.to_string().to_uppercase()).bold()
+    );
+    if plan.grow > 0 {
+        println!("  grow the image by {}", format_size(plan.grow));
+    }
+    if plan.from_table != plan.to_table {
+        println!(
+            "  convert the partition table from {} to {}",
+            plan.from_table, plan.to_table
+        );
+    }
+    for (old, new) in plan.renumbered() {
+        println!("  partition {} becomes partition {}", old, new);
+    }
+    if let Some(esp) = &plan.new_esp {
+        println!(
+            "  create ESP partition {} at offset {} ({})",
+            esp.number,
+            esp.start,
+            format_size(esp.size)
+        );
+        println!("  install GRUB (or shim) to the ESP and mount it at /boot/efi");
+    } else {
+        println!("  install GRUB to the MBR");
+    }
+    if let Some(active) = plan.active {
+        println!("  mark partition {} active", active);
+    }
+    println!();
+}
+
+fn print_report(report: &BootRepairReport) {
+    for change in &report.changes {
+        println!("  {} {}", plain::text("✓").green(), change);
+    }
+    for warning in &report.warnings {
+        println!("  {} {}", "!".yellow(), warning);
+    }
+}
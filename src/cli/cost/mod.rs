@@ -5,6 +5,8 @@ pub mod analyzer;
 pub mod estimator;
 pub mod reporter;
 
+use crate::cli::plain;
+use crate::cli::target;
 use anyhow::Result;
 use guestkit::Guestfs;
 use serde::{Deserialize, Serialize};
@@ -157,7 +159,7 @@ pub fn analyze_costs<P: AsRef<Path>>(
     let image_path_str = image_path.as_ref().display().to_string();
 
     if verbose {
-        println!("💰 Analyzing costs for: {}", image_path_str);
+        println!("{}Analyzing costs for: {}", plain::text("💰 "), image_path_str);
         println!("   Provider: {}", provider.as_str());
         println!("   Region: {}", region);
     }
@@ -230,6 +232,8 @@ fn extract_metrics<P: AsRef<Path>>(image_path: P, verbose: bool) -> Result<Syste
     // Initialize guestfs
     let mut g = Guestfs::new()?;
     g.add_drive_opts(&image_path, true, None)?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
     g.launch()?;
 
     // Inspect OS
@@ -241,10 +245,7 @@ fn extract_metrics<P: AsRef<Path>>(image_path: P, verbose: bool) -> Result<Syste
     let root = &roots[0];
 
     // Mount filesystems
-    let mountpoints = g.inspect_get_mountpoints(root)?;
-    for (mp, dev) in mountpoints {
-        let _ = g.mount(&dev, &mp);
-    }
+    g.mount_os_ro(root)?;
 
     // Get system info
     let applications = g.inspect_list_applications2(root)?;
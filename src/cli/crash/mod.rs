@@ -0,0 +1,180 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Crash dump and core file evidence collection module
+
+pub mod reporter;
+pub mod scanner;
+
+use crate::cli::plain;
+use crate::cli::target;
+use anyhow::Result;
+use guestkit::Guestfs;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Source of a piece of crash evidence
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum CrashKind {
+    /// kdump vmcore under /var/crash
+    KernelDump,
+    /// systemd-coredump entry under /var/lib/systemd/coredump
+    SystemdCoredump,
+    /// Ubuntu apport report (/var/crash/*.crash)
+    ApportReport,
+    /// ABRT problem directory (/var/spool/abrt)
+    AbrtReport,
+    /// Plain `core` / `core.<pid>` file
+    CoreFile,
+}
+
+impl CrashKind {
+    pub fn emoji(&self) -> &str {
+        match self {
+            Self::KernelDump => "💥",
+            Self::SystemdCoredump => "🧩",
+            Self::ApportReport => "📝",
+            Self::AbrtReport => "📝",
+            Self::CoreFile => "🗑️",
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::KernelDump => "kernel-dump",
+            Self::SystemdCoredump => "systemd-coredump",
+            Self::ApportReport => "apport",
+            Self::AbrtReport => "abrt",
+            Self::CoreFile => "core-file",
+        }
+    }
+
+    /// Whether this evidence points at a kernel (rather than userspace) failure
+    pub fn is_kernel(&self) -> bool {
+        matches!(self, Self::KernelDump)
+    }
+}
+
+/// A single crash artifact found in the guest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashEvidence {
+    pub kind: CrashKind,
+    /// Guest path of the dump file or problem directory
+    pub path: String,
+    /// Crash time (Unix seconds), falling back to the artifact mtime
+    pub timestamp: Option<i64>,
+    /// Offending binary, when known
+    pub binary: Option<String>,
+    pub signal: Option<String>,
+    pub pid: Option<u32>,
+    pub uid: Option<u32>,
+    /// Short description (panic message, crash reason, ...)
+    pub summary: String,
+    pub size_bytes: i64,
+}
+
+impl CrashEvidence {
+    pub fn time_string(&self) -> String {
+        self.timestamp
+            .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+            .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_else(|| "unknown".to_string())
+    }
+}
+
+/// Crash evidence summary
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct CrashSummary {
+    pub total: usize,
+    pub kernel_dumps: usize,
+    pub userspace_crashes: usize,
+    pub first_crash: Option<String>,
+    pub last_crash: Option<String>,
+    /// Binaries ordered by crash count
+    pub top_binaries: Vec<(String, usize)>,
+}
+
+/// Crash evidence report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct CrashReport {
+    pub image_path: String,
+    pub scanned_at: String,
+    pub evidence: Vec<CrashEvidence>,
+    pub summary: CrashSummary,
+    /// Host paths of extracted artifacts
+    pub extracted: Vec<String>,
+}
+
+/// Scan disk image for crash dumps, optionally extracting them to `extract_dir`
+pub fn scan_crashes<P: AsRef<Path>>(
+    image_path: P,
+    extract_dir: Option<&Path>,
+    verbose: bool,
+) -> Result<CrashReport> {
+    let image_path_str = image_path.as_ref().display().to_string();
+
+    if verbose {
+        println!("{}Collecting crash evidence in: {}", plain::text("💥 "), image_path_str);
+    }
+
+    // Initialize guestfs
+    let mut g = Guestfs::new()?;
+    g.add_drive_opts(&image_path, true, None)?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
+    g.launch()?;
+
+    // Inspect OS
+    let roots = g.inspect_os()?;
+    if roots.is_empty() {
+        anyhow::bail!("No operating systems found in disk image");
+    }
+
+    let root = &roots[0];
+
+    // Mount filesystems
+    g.mount_os_ro(root)?;
+
+    let evidence = scanner::collect_evidence(&mut g);
+
+    let extracted = match extract_dir {
+        Some(dir) => scanner::extract_evidence(&mut g, &evidence, dir, verbose)?,
+        None => Vec::new(),
+    };
+
+    // Shutdown guestfs
+    g.shutdown()?;
+
+    let summary = summarize(&evidence);
+
+    Ok(CrashReport {
+        image_path: image_path_str,
+        scanned_at: chrono::Utc::now().to_rfc3339(),
+        evidence,
+        summary,
+        extracted,
+    })
+}
+
+/// Build summary statistics for a set of crash artifacts
+pub fn summarize(evidence: &[CrashEvidence]) -> CrashSummary {
+    let kernel_dumps = evidence.iter().filter(|e| e.kind.is_kernel()).count();
+
+    let first = evidence.iter().filter(|e| e.timestamp.is_some()).min_by_key(|e| e.timestamp);
+    let last = evidence.iter().filter(|e| e.timestamp.is_some()).max_by_key(|e| e.timestamp);
+
+    let mut binaries: HashMap<String, usize> = HashMap::new();
+    for binary in evidence.iter().filter_map(|e| e.binary.as_ref()) {
+        *binaries.entry(binary.clone()).or_insert(0) += 1;
+    }
+    let mut top_binaries: Vec<(String, usize)> = binaries.into_iter().collect();
+    top_binaries.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    CrashSummary {
+        total: evidence.len(),
+        kernel_dumps,
+        userspace_crashes: evidence.len() - kernel_dumps,
+        first_crash: first.map(|e| e.time_string()),
+        last_crash: last.map(|e| e.time_string()),
+        top_binaries,
+    }
+}
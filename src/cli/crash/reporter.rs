@@ -0,0 +1,90 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Crash evidence report formatting
+
+use super::CrashReport;
+
+/// Format crash report as text
+pub fn format_report(report: &CrashReport) -> String {
+    let mut output = String::new();
+
+    output.push_str("💥 Crash Evidence Report\n");
+    output.push_str("========================\n\n");
+    output.push_str(&format!("Image: {}\n", report.image_path));
+    output.push_str(&format!("Scanned: {}\n\n", report.scanned_at));
+
+    if report.evidence.is_empty() {
+        output.push_str("✅ No crash dumps or core files found\n");
+        return output;
+    }
+
+    // Summary
+    output.push_str("📊 Summary\n");
+    output.push_str("----------\n");
+    output.push_str(&format!("Total artifacts: {}\n", report.summary.total));
+    output.push_str(&format!("Kernel crashes: {}\n", report.summary.kernel_dumps));
+    output.push_str(&format!("Userspace crashes: {}\n", report.summary.userspace_crashes));
+    if let Some(ref first) = report.summary.first_crash {
+        output.push_str(&format!("First crash: {}\n", first));
+    }
+    if let Some(ref last) = report.summary.last_crash {
+        output.push_str(&format!("Last crash: {}\n", last));
+    }
+    output.push('\n');
+
+    // Offending binaries
+    if !report.summary.top_binaries.is_empty() {
+        output.push_str("🎯 Offending Binaries\n");
+        output.push_str("---------------------\n");
+        for (binary, count) in report.summary.top_binaries.iter().take(10) {
+            output.push_str(&format!("{}: {}\n", binary, count));
+        }
+        output.push('\n');
+    }
+
+    // Chronological evidence
+    output.push_str("📅 Crash Timeline\n");
+    output.push_str("-----------------\n");
+    for item in &report.evidence {
+        output.push_str(&format!(
+            "{} {} [{}] {}\n",
+            item.kind.emoji(),
+            item.time_string(),
+            item.kind.as_str(),
+            item.summary
+        ));
+        output.push_str(&format!(
+            "   └─ {} ({:.2} MB)\n",
+            item.path,
+            item.size_bytes as f64 / 1_048_576.0
+        ));
+    }
+    output.push('\n');
+
+    if !report.extracted.is_empty() {
+        output.push_str(&format!("📦 Extracted {} files\n", report.extracted.len()));
+    }
+
+    output
+}
+
+/// Format crash report as CSV
+pub fn format_csv(report: &CrashReport) -> String {
+    let mut output = String::from("Time,Kind,Path,Binary,PID,UID,Signal,Size,Summary\n");
+
+    for item in &report.evidence {
+        output.push_str(&format!(
+            "{},{},\"{}\",\"{}\",{},{},{},{},\"{}\"\n",
+            item.time_string(),
+            item.kind.as_str(),
+            item.path,
+            item.binary.as_deref().unwrap_or(""),
+            item.pid.map(|p| p.to_string()).unwrap_or_default(),
+            item.uid.map(|u| u.to_string()).unwrap_or_default(),
+            item.signal.as_deref().unwrap_or(""),
+            item.size_bytes,
+            item.summary.replace('"', "\"\"")
+        ));
+    }
+
+    output
+}
@@ -0,0 +1,453 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Crash artifact discovery and parsing
+
+use super::{CrashEvidence, CrashKind};
+use anyhow::Result;
+use chrono::NaiveDateTime;
+use guestkit::Guestfs;
+use std::collections::HashMap;
+use std::path::Path;
+
+/// kdump and apport output directory
+const VAR_CRASH_DIR: &str = "/var/crash";
+
+/// systemd-coredump storage directory
+const COREDUMP_DIR: &str = "/var/lib/systemd/coredump";
+
+/// ABRT problem directories
+const ABRT_DIRS: &[&str] = &["/var/spool/abrt", "/var/tmp/abrt"];
+
+/// Directories where processes commonly drop plain core files
+const CORE_FILE_DIRS: &[&str] = &["/", "/root", "/tmp", "/var/tmp"];
+
+/// Collect all crash evidence from a mounted guest, oldest first
+///
+/// Individual sources that are missing or unreadable are skipped.
+pub fn collect_evidence(g: &mut Guestfs) -> Vec<CrashEvidence> {
+    let mut evidence = Vec::new();
+
+    evidence.extend(scan_var_crash(g));
+    evidence.extend(scan_systemd_coredumps(g));
+    evidence.extend(scan_abrt(g));
+    evidence.extend(scan_core_files(g));
+
+    evidence.sort_by_key(|e| e.timestamp);
+    evidence
+}
+
+/// Copy crash artifacts out of the guest into `dest`
+pub fn extract_evidence(
+    g: &mut Guestfs,
+    evidence: &[CrashEvidence],
+    dest: &Path,
+    verbose: bool,
+) -> Result<Vec<String>> {
+    std::fs::create_dir_all(dest)?;
+
+    let mut extracted = Vec::new();
+    for item in evidence {
+        let target = dest.join(flatten_path(&item.path));
+
+        let files: Vec<(String, std::path::PathBuf)> = if g.is_dir(&item.path).unwrap_or(false) {
+            std::fs::create_dir_all(&target)?;
+            list_dir(g, &item.path)
+                .into_iter()
+                .map(|name| (format!("{}/{}", item.path, name), target.join(&name)))
+                .collect()
+        } else {
+            vec![(item.path.clone(), target)]
+        };
+
+        for (guest_path, host_path) in files {
+            if !g.is_file(&guest_path).unwrap_or(false) {
+                continue;
+            }
+            if verbose {
+                println!("  Extracting {}", guest_path);
+            }
+            g.download(&guest_path, &host_path.to_string_lossy())?;
+            extracted.push(host_path.display().to_string());
+        }
+    }
+
+    Ok(extracted)
+}
+
+fn flatten_path(path: &str) -> String {
+    path.trim_start_matches('/').replace('/', "_")
+}
+
+fn list_dir(g: &mut Guestfs, dir: &str) -> Vec<String> {
+    if !g.is_dir(dir).unwrap_or(false) {
+        return Vec::new();
+    }
+    g.ls(dir).unwrap_or_default()
+}
+
+fn read_small(g: &mut Guestfs, path: &str) -> Option<String> {
+    if !g.is_file(path).unwrap_or(false) {
+        return None;
+    }
+    g.cat(path).ok().map(|s| s.trim().to_string())
+}
+
+fn scan_var_crash(g: &mut Guestfs) -> Vec<CrashEvidence> {
+    let mut evidence = Vec::new();
+
+    for name in list_dir(g, VAR_CRASH_DIR) {
+        let path = format!("{}/{}", VAR_CRASH_DIR, name);
+
+        if g.is_dir(&path).unwrap_or(false) {
+            let files = list_dir(g, &path);
+            let dumps: Vec<&String> = files
+                .iter()
+                .filter(|f| (f.starts_with("vmcore") && !f.contains("dmesg")) || f.starts_with("dump."))
+                .collect();
+            if dumps.is_empty() {
+                continue;
+            }
+
+            let size_bytes = dumps
+                .iter()
+                .filter_map(|f| g.stat(&format!("{}/{}", path, f)).ok())
+                .map(|s| s.size)
+                .sum();
+
+            let summary = files
+                .iter()
+                .find(|f| *f == "vmcore-dmesg.txt" || f.starts_with("dmesg."))
+                .and_then(|f| read_small(g, &format!("{}/{}", path, f)))
+                .and_then(|dmesg| parse_panic_message(&dmesg))
+                .unwrap_or_else(|| "Kernel crash dump (no dmesg captured)".to_string());
+
+            let timestamp = parse_kdump_dir_time(&name)
+                .or_else(|| g.stat(&path).ok().map(|s| s.mtime));
+
+            evidence.push(CrashEvidence {
+                kind: CrashKind::KernelDump,
+                path,
+                timestamp,
+                binary: Some("vmlinux".to_string()),
+                signal: None,
+                pid: None,
+                uid: None,
+                summary,
+                size_bytes,
+            });
+        } else if name.ends_with(".crash") {
+            // Only the header fields are needed; the core is base64 further down
+            let Ok(lines) = g.head_n(80, &path) else {
+                continue;
+            };
+            let fields = parse_apport_header(&lines);
+            let stat = g.stat(&path).ok();
+
+            let problem_type = fields.get("ProblemType").cloned().unwrap_or_default();
+            let kind = if problem_type.starts_with("Kernel") {
+                CrashKind::KernelDump
+            } else {
+                CrashKind::ApportReport
+            };
+
+            evidence.push(CrashEvidence {
+                kind,
+                timestamp: fields
+                    .get("Date")
+                    .and_then(|d| parse_apport_date(d))
+                    .or_else(|| stat.as_ref().map(|s| s.mtime)),
+                binary: fields.get("ExecutablePath").cloned(),
+                signal: fields.get("Signal").cloned(),
+                pid: None,
+                uid: None,
+                summary: fields
+                    .get("Title")
+                    .cloned()
+                    .unwrap_or_else(|| format!("apport {} report", problem_type)),
+                size_bytes: stat.map(|s| s.size).unwrap_or(0),
+                path,
+            });
+        }
+    }
+
+    evidence
+}
+
+fn scan_systemd_coredumps(g: &mut Guestfs) -> Vec<CrashEvidence> {
+    let mut evidence = Vec::new();
+
+    for name in list_dir(g, COREDUMP_DIR) {
+        let Some(core) = parse_coredump_name(&name) else {
+            continue;
+        };
+        let path = format!("{}/{}", COREDUMP_DIR, name);
+        let stat = g.stat(&path).ok();
+
+        evidence.push(CrashEvidence {
+            kind: CrashKind::SystemdCoredump,
+            timestamp: core.timestamp.or_else(|| stat.as_ref().map(|s| s.mtime)),
+            summary: format!("{} (pid {}) dumped core", core.comm, core.pid),
+            binary: Some(core.comm),
+            signal: None,
+            pid: Some(core.pid),
+            uid: Some(core.uid),
+            size_bytes: stat.map(|s| s.size).unwrap_or(0),
+            path,
+        });
+    }
+
+    evidence
+}
+
+fn scan_abrt(g: &mut Guestfs) -> Vec<CrashEvidence> {
+    let mut evidence = Vec::new();
+
+    for base in ABRT_DIRS {
+        for name in list_dir(g, base) {
+            let path = format!("{}/{}", base, name);
+            if !g.is_dir(&path).unwrap_or(false) {
+                continue;
+            }
+
+            let analyzer = read_small(g, &format!("{}/analyzer", path)).unwrap_or_default();
+            let Some(reason) = read_small(g, &format!("{}/reason", path)) else {
+                continue;
+            };
+
+            let kind = if analyzer == "vmcore" || analyzer.eq_ignore_ascii_case("kerneloops") {
+                CrashKind::KernelDump
+            } else {
+                CrashKind::AbrtReport
+            };
+
+            evidence.push(CrashEvidence {
+                kind,
+                timestamp: read_small(g, &format!("{}/time", path)).and_then(|t| t.parse().ok()),
+                binary: read_small(g, &format!("{}/executable", path)),
+                signal: read_small(g, &format!("{}/signal", path)),
+                pid: read_small(g, &format!("{}/pid", path)).and_then(|p| p.parse().ok()),
+                uid: read_small(g, &format!("{}/uid", path)).and_then(|u| u.parse().ok()),
+                summary: reason,
+                size_bytes: g
+                    .stat(&format!("{}/coredump", path))
+                    .map(|s| s.size)
+                    .unwrap_or(0),
+                path,
+            });
+        }
+    }
+
+    evidence
+}
+
+fn scan_core_files(g: &mut Guestfs) -> Vec<CrashEvidence> {
+    let mut dirs: Vec<String> = CORE_FILE_DIRS.iter().map(|s| s.to_string()).collect();
+    dirs.extend(list_dir(g, "/home").into_iter().map(|user| format!("/home/{}", user)));
+
+    let mut evidence = Vec::new();
+    for dir in dirs {
+        for name in list_dir(g, &dir) {
+            let Some(pid) = parse_core_file_name(&name) else {
+                continue;
+            };
+            let path = format!("{}/{}", dir.trim_end_matches('/'), name);
+            if !g.is_file(&path).unwrap_or(false) {
+                continue;
+            }
+            let Ok(stat) = g.stat(&path) else {
+                continue;
+            };
+
+            evidence.push(CrashEvidence {
+                kind: CrashKind::CoreFile,
+                timestamp: Some(stat.mtime),
+                binary: None,
+                signal: None,
+                pid,
+                uid: Some(stat.uid),
+                summary: format!("core file in {}", dir),
+                size_bytes: stat.size,
+                path,
+            });
+        }
+    }
+
+    evidence
+}
+
+/// Fields encoded in a systemd-coredump file name
+#[derive(Debug, PartialEq)]
+struct CoredumpName {
+    comm: String,
+    uid: u32,
+    pid: u32,
+    timestamp: Option<i64>,
+}
+
+/// Parse `core.<comm>.<uid>.<boot-id>.<pid>.<usec>[.zst|.xz|.lz4]`
+fn parse_coredump_name(name: &str) -> Option<CoredumpName> {
+    let name = name.strip_prefix("core.")?;
+    let name = [".zst", ".xz", ".lz4"]
+        .iter()
+        .find_map(|ext| name.strip_suffix(ext))
+        .unwrap_or(name);
+
+    // The command name may itself contain dots, so parse from the right
+    let mut parts = name.rsplitn(5, '.');
+    let usec: i64 = parts.next()?.parse().ok()?;
+    let pid = parts.next()?.parse().ok()?;
+    let boot_id = parts.next()?;
+    let uid = parts.next()?.parse().ok()?;
+    let comm = parts.next()?;
+
+    if boot_id.len() != 32 || comm.is_empty() {
+        return None;
+    }
+
+    Some(CoredumpName {
+        comm: comm.replace("\\x2d", "-"),
+        uid,
+        pid,
+        timestamp: Some(usec / 1_000_000),
+    })
+}
+
+/// Match `core` or `core.<pid>`, returning the pid when present
+fn parse_core_file_name(name: &str) -> Option<Option<u32>> {
+    if name == "core" {
+        return Some(None);
+    }
+    name.strip_prefix("core.")?.parse().ok().map(Some)
+}
+
+/// Parse kdump directory names (`127.0.0.1-2024-01-15-10:22:33` or `202401151022`)
+fn parse_kdump_dir_time(name: &str) -> Option<i64> {
+    if name.len() >= 19 {
+        let tail = &name[name.len() - 19..];
+        if let Ok(dt) = NaiveDateTime::parse_from_str(tail, "%Y-%m-%d-%H:%M:%S") {
+            return Some(dt.and_utc().timestamp());
+        }
+    }
+
+    if name.len() == 12 && name.chars().all(|c| c.is_ascii_digit()) {
+        let with_seconds = format!("{}00", name);
+        if let Ok(dt) = NaiveDateTime::parse_from_str(&with_seconds, "%Y%m%d%H%M%S") {
+            return Some(dt.and_utc().timestamp());
+        }
+    }
+
+    None
+}
+
+/// Parse the apport `Date:` field (e.g. `Tue Jan 16 10:22:33 2024`)
+fn parse_apport_date(date: &str) -> Option<i64> {
+    NaiveDateTime::parse_from_str(date.trim(), "%a %b %e %H:%M:%S %Y")
+        .ok()
+        .map(|dt| dt.and_utc().timestamp())
+}
+
+/// Parse the single-line `Key: value` fields of an apport report
+fn parse_apport_header(lines: &[String]) -> HashMap<String, String> {
+    lines
+        .iter()
+        .filter(|line| !line.starts_with(' '))
+        .filter_map(|line| line.split_once(": "))
+        .map(|(key, value)| (key.to_string(), value.trim().to_string()))
+        .collect()
+}
+
+/// Extract the most relevant panic/oops line from a kernel log
+fn parse_panic_message(dmesg: &str) -> Option<String> {
+    const MARKERS: &[&str] = &[
+        "Kernel panic",
+        "BUG:",
+        "Oops",
+        "general protection fault",
+        "Unable to handle kernel",
+    ];
+
+    dmesg
+        .lines()
+        .find(|line| MARKERS.iter().any(|m| line.contains(m)))
+        .map(|line| {
+            // Drop the "[  123.456789] " printk timestamp
+            match line.trim_start().strip_prefix('[') {
+                Some(rest) => rest.split_once(']').map(|(_, msg)| msg).unwrap_or(rest),
+                None => line,
+            }
+            .trim()
+            .to_string()
+        })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_coredump_name() {
+        let core = parse_coredump_name(
+            "core.nginx.0.5f3c2a1b9d8e4f7a6b5c4d3e2f1a0b9c.1234.1705400553000000.zst",
+        )
+        .unwrap();
+        assert_eq!(core.comm, "nginx");
+        assert_eq!(core.uid, 0);
+        assert_eq!(core.pid, 1234);
+        assert_eq!(core.timestamp, Some(1705400553));
+
+        let dotted = parse_coredump_name(
+            "core.python3.11.1000.5f3c2a1b9d8e4f7a6b5c4d3e2f1a0b9c.42.1705400553000000",
+        )
+        .unwrap();
+        assert_eq!(dotted.comm, "python3.11");
+        assert_eq!(dotted.uid, 1000);
+
+        assert!(parse_coredump_name("core.bogus").is_none());
+    }
+
+    #[test]
+    fn test_parse_core_file_name() {
+        assert_eq!(parse_core_file_name("core"), Some(None));
+        assert_eq!(parse_core_file_name("core.4242"), Some(Some(4242)));
+        assert_eq!(parse_core_file_name("core.py"), None);
+        assert_eq!(parse_core_file_name("corefile"), None);
+    }
+
+    #[test]
+    fn test_parse_kdump_dir_time() {
+        assert_eq!(parse_kdump_dir_time("127.0.0.1-2024-01-16-10:22:33"), Some(1705400553));
+        assert_eq!(parse_kdump_dir_time("202401161022"), Some(1705400520));
+        assert_eq!(parse_kdump_dir_time("lost+found"), None);
+    }
+
+    #[test]
+    fn test_parse_apport() {
+        let lines: Vec<String> = [
+            "ProblemType: Crash",
+            "Date: Tue Jan 16 10:22:33 2024",
+            "ExecutablePath: /usr/bin/gnome-shell",
+            "Signal: 11",
+            "CoreDump: base64",
+            " H4sICAAAAAAC/0NvcmVEdW1wAA==",
+        ]
+        .iter()
+        .map(|s| s.to_string())
+        .collect();
+
+        let fields = parse_apport_header(&lines);
+        assert_eq!(fields.get("ExecutablePath").unwrap(), "/usr/bin/gnome-shell");
+        assert_eq!(fields.get("Signal").unwrap(), "11");
+        assert_eq!(parse_apport_date(fields.get("Date").unwrap()), Some(1705400553));
+    }
+
+    #[test]
+    fn test_parse_panic_message() {
+        let dmesg = "[    0.000000] Linux version 6.1.0\n\
+                     [  812.331245] Kernel panic - not syncing: Fatal exception\n\
+                     [  812.331300] CPU: 1 PID: 0 Comm: swapper/1\n";
+        assert_eq!(
+            parse_panic_message(dmesg).unwrap(),
+            "Kernel panic - not syncing: Fatal exception"
+        );
+        assert!(parse_panic_message("all quiet").is_none());
+    }
+}
@@ -0,0 +1,345 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Customize command - offline guest customization, like virt-customize
+
+use crate::cli::audit_log::audited;
+use crate::cli::catalog::parse_image_ref;
+use crate::cli::plain;
+use crate::cli::target;
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use colored::*;
+use guestkit::guestfs::{CustomizeOp, CustomizeReport};
+use guestkit::Guestfs;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Args)]
+pub struct CustomizeCommand {
+    /// Disk image to customize
+    #[arg(value_parser = parse_image_ref)]
+    pub image: PathBuf,
+
+    /// Set the hostname
+    #[arg(long)]
+    pub hostname: Option<String>,
+
+    /// Add a user with a private group: NAME or NAME:GROUP,GROUP...
+    #[arg(long = "add-user", value_name = "NAME[:GROUPS]")]
+    pub add_users: Vec<String>,
+
+    /// Set a user's password hash, as made by 'openssl passwd -6'
+    #[arg(long = "password-hash", value_name = "USER:HASH")]
+    pub password_hashes: Vec<String>,
+
+    /// Set root's password hash
+    #[arg(long, value_name = "HASH")]
+    pub root_password_hash: Option<String>,
+
+    /// Authorize an SSH key: USER (your default key), USER:file:PATH or
+    /// USER:string:KEY
+    #[arg(long = "ssh-inject", value_name = "USER[:SELECTOR]")]
+    pub ssh_keys: Vec<String>,
+
+    /// Write a file in the guest
+    #[arg(long = "write", value_name = "PATH:CONTENT")]
+    pub writes: Vec<String>,
+
+    /// Copy a host file into the guest
+    #[arg(long = "upload", value_name = "FILE:PATH")]
+    pub uploads: Vec<String>,
+
+    /// Run a host script in the guest when it first boots
+    #[arg(long = "firstboot", value_name = "SCRIPT")]
+    pub firstboot_scripts: Vec<PathBuf>,
+
+    /// Run a shell command in the guest when it first boots
+    #[arg(long = "firstboot-command", value_name = "COMMAND")]
+    pub firstboot_commands: Vec<String>,
+
+    /// Seed cloud-init (NoCloud) with this user-data file
+    #[arg(long, value_name = "FILE")]
+    pub cloud_init_user_data: Option<PathBuf>,
+
+    /// NoCloud meta-data file (default: a new instance-id)
+    #[arg(long, value_name = "FILE", requires = "cloud_init_user_data")]
+    pub cloud_init_meta_data: Option<PathBuf>,
+
+    /// NoCloud network-config file
+    #[arg(long, value_name = "FILE", requires = "cloud_init_user_data")]
+    pub cloud_init_network_config: Option<PathBuf>,
+
+    /// JSON or YAML list of operations, applied after the other options
+    #[arg(long, value_name = "FILE")]
+    pub ops: Option<PathBuf>,
+
+    /// Show the operations without changing the image
+    #[arg(long)]
+    pub dry_run: bool,
+
+    /// Change the ext4 root filesystem of a raw image in pure Rust, without
+    /// launching; supports --write, --upload and password hashes
+    #[arg(long)]
+    pub enable_writes: bool,
+
+    /// Output format (text, json)
+    #[arg(short, long, default_value = "text")]
+    pub format: String,
+
+    /// Enable verbose output
+    #[arg(short, long)]
+    pub verbose: bool,
+}
+
+impl CustomizeCommand {
+    pub fn execute(&self) -> Result<()> {
+        let ops = self.operations()?;
+        if ops.is_empty() {
+            bail!("Nothing to do; see 'guestctl customize --help' for the operations");
+        }
+
+        if self.dry_run {
+            if self.format == "json" {
+                println!("{}", serde_json::to_string_pretty(&ops)?);
+            } else {
+                println!("{}", self.image.display().to_string().bold());
+                for op in &ops {
+                    println!("  {}", op);
+                }
+            }
+            return Ok(());
+        }
+
+        // Descriptions only: the operations carry password hashes and files
+        let descriptions: Vec<String> = ops.iter().map(|op| op.to_string()).collect();
+        let report = audited(
+            "customize",
+            &self.image,
+            json!({ "operations": descriptions }),
+            || self.customize(&ops),
+        )?;
+
+        if self.format == "json" {
+            println!("{}", serde_json::to_string_pretty(&report)?);
+        } else {
+            for change in &report.changes {
+                println!("  {} {}", plain::text("✓").green(), change);
+            }
+            for warning in &report.warnings {
+                println!("  {} {}", "!".yellow(), warning);
+            }
+            eprintln!("{} Customized {}", plain::text("✓").green(), self.image.display());
+        }
+        Ok(())
+    }
+
+    fn customize(&self, ops: &[CustomizeOp]) -> Result<CustomizeReport> {
+        let mut g = Guestfs::new()?;
+        g.set_verbose(self.verbose);
+        g.add_drive(self.image.to_str().context("Image path is not UTF-8")?)?;
+        target::add_disks(&mut g, false)?;
+        target::select(&mut g);
+        if self.enable_writes {
+            g.set_enable_writes(true);
+            let root = g.ext4_find_root()?;
+            return Ok(g.customize_ext4(&root, ops)?);
+        }
+        g.launch()?;
+        let roots = g.inspect_os()?;
+        let root = roots.first().context("No operating system found")?;
+        g.mount_os_rw(root)?;
+
+        let report = g.customize(root, ops);
+        g.umount_all().ok();
+        g.shutdown().ok();
+        Ok(report?)
+    }
+
+    /// The operations, in the order they run
+    fn operations(&self) -> Result<Vec<CustomizeOp>> {
+        let mut ops = Vec::new();
+        if let Some(hostname) = &self.hostname {
+            ops.push(CustomizeOp::Hostname {
+                hostname: hostname.clone(),
+            });
+        }
+        for user in &self.add_users {
+            ops.push(parse_add_user(user));
+        }
+        if let Some(hash) = &self.root_password_hash {
+            ops.push(CustomizeOp::PasswordHash {
+                user: "root".to_string(),
+                hash: hash.clone(),
+            });
+        }
+        for value in &self.password_hashes {
+            let (user, hash) = split_pair(value, "--password-hash", "USER:HASH")?;
+            ops.push(CustomizeOp::PasswordHash { user, hash });
+        }
+        for value in &self.ssh_keys {
+            ops.push(parse_ssh_inject(value)?);
+        }
+        for value in &self.writes {
+            let (path, content) = split_pair(value, "--write", "PATH:CONTENT")?;
+            ops.push(CustomizeOp::WriteFile {
+                path,
+                content,
+                mode: None,
+            });
+        }
+        for value in &self.uploads {
+            let (source, path) = split_pair(value, "--upload", "FILE:PATH")?;
+            if !Path::new(&source).is_file() {
+                bail!("--upload: {} is not a file", source);
+            }
+            ops.push(CustomizeOp::Upload {
+                source,
+                path,
+                mode: None,
+            });
+        }
+        for script in &self.firstboot_scripts {
+            ops.push(CustomizeOp::FirstBoot {
+                name: script
+                    .file_name()
+                    .map(|name| name.to_string_lossy().into_owned())
+                    .unwrap_or_else(|| "script".to_string()),
+                script: read(script)?,
+            });
+        }
+        for command in &self.firstboot_commands {
+            ops.push(CustomizeOp::FirstBoot {
+                name: "command".to_string(),
+                script: format!("#!/bin/sh\n{}\n", command),
+            });
+        }
+        if let Some(user_data) = &self.cloud_init_user_data {
+            ops.push(CustomizeOp::CloudInit {
+                user_data: read(user_data)?,
+                meta_data: self.cloud_init_meta_data.as_deref().map(read).transpose()?,
+                network_config: self
+                    .cloud_init_network_config
+                    .as_deref()
+                    .map(read)
+                    .transpose()?,
+            });
+        }
+        if let Some(path) = &self.ops {
+            ops.extend(parse_ops_file(&read(path)?).with_context(|| {
+                format!("Failed to parse the operations in {}", path.display())
+            })?);
+        }
+        Ok(ops)
+    }
+}
+
+fn read(path: &Path) -> Result<String> {
+    std::fs::read_to_string(path).with_context(|| format!("Failed to read {}", path.display()))
+}
+
+fn split_pair(value: &str, option: &str, syntax: &str) -> Result<(String, String)> {
+    match value.split_once(':') {
+        Some((first, second)) if !first.is_empty() => Ok((first.to_string(), second.to_string())),
+        _ => bail!("{} expects {}, got '{}'", option, syntax, value),
+    }
+}
+
+/// NAME or NAME:GROUP,GROUP...
+fn parse_add_user(value: &str) -> CustomizeOp {
+    let (name, groups) = value.split_once(':').unwrap_or((value, ""));
+    CustomizeOp::User {
+        name: name.to_string(),
+        uid: None,
+        groups: groups
+            .split(',')
+            .filter(|group| !group.is_empty())
+            .map(String::from)
+            .collect(),
+        shell: None,
+        password_hash: None,
+    }
+}
+
+/// USER, USER:file:PATH or USER:string:KEY
+fn parse_ssh_inject(value: &str) -> Result<CustomizeOp> {
+    let mut parts = value.splitn(3, ':');
+    let user = parts.next().unwrap_or_default().to_string();
+    let key = match (parts.next(), parts.next()) {
+        (None, _) => default_public_key()?,
+        (Some("file"), Some(path)) => read(Path::new(path))?,
+        (Some("string"), Some(key)) => key.to_string(),
+        _ => bail!(
+            "--ssh-inject expects USER, USER:file:PATH or USER:string:KEY, got '{}'",
+            value
+        ),
+    };
+    if user.is_empty() || key.trim().is_empty() {
+        bail!("--ssh-inject: no user or key in '{}'", value);
+    }
+    Ok(CustomizeOp::SshKey {
+        user,
+        key: key.trim().to_string(),
+    })
+}
+
+/// The public key of the invoking user
+fn default_public_key() -> Result<String> {
+    let ssh_dir = dirs::home_dir()
+        .context("Could not determine home directory")?
+        .join(".ssh");
+    ["id_ed25519.pub", "id_ecdsa.pub", "id_rsa.pub"]
+        .iter()
+        .map(|name| ssh_dir.join(name))
+        .find(|path| path.is_file())
+        .map(|path| read(&path))
+        .unwrap_or_else(|| bail!("No public key in {}", ssh_dir.display()))
+}
+
+/// JSON and YAML both parse as YAML
+fn parse_ops_file(content: &str) -> Result<Vec<CustomizeOp>> {
+    Ok(serde_yaml::from_str(content)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_options() {
+        assert_eq!(
+            parse_add_user("deploy:wheel,docker"),
+            CustomizeOp::User {
+                name: "deploy".to_string(),
+                uid: None,
+                groups: vec!["wheel".to_string(), "docker".to_string()],
+                shell: None,
+                password_hash: None,
+            }
+        );
+        assert_eq!(
+            parse_ssh_inject("root:string:ssh-ed25519 AAAA ops@example.com").unwrap(),
+            CustomizeOp::SshKey {
+                user: "root".to_string(),
+                key: "ssh-ed25519 AAAA ops@example.com".to_string(),
+            }
+        );
+        assert!(parse_ssh_inject("root:url:https://example.com/key").is_err());
+        assert_eq!(
+            split_pair("/etc/motd:Welcome: staff only", "--write", "PATH:CONTENT").unwrap(),
+            ("/etc/motd".to_string(), "Welcome: staff only".to_string())
+        );
+        assert!(split_pair("no-colon", "--write", "PATH:CONTENT").is_err());
+    }
+
+    #[test]
+    fn test_parse_ops_file() {
+        let yaml = "- op: hostname\n  hostname: web-01\n\
+                    - op: write-file\n  path: /etc/motd\n  content: hello\n";
+        let ops = parse_ops_file(yaml).unwrap();
+        assert_eq!(ops.len(), 2);
+        assert_eq!(ops[0].to_string(), "set hostname to web-01");
+
+        let json = r#"[{"op": "password-hash", "user": "root", "hash": "!"}]"#;
+        assert_eq!(parse_ops_file(json).unwrap().len(), 1);
+        assert!(parse_ops_file("- op: reboot\n").is_err());
+    }
+}
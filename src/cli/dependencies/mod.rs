@@ -5,6 +5,8 @@ pub mod analyzer;
 pub mod graph;
 pub mod visualizer;
 
+use crate::cli::plain;
+use crate::cli::target;
 use anyhow::Result;
 use guestkit::Guestfs;
 use serde::{Deserialize, Serialize};
@@ -106,12 +108,14 @@ pub fn analyze_dependencies<P: AsRef<Path>>(
     let image_path_str = image_path.as_ref().display().to_string();
 
     if verbose {
-        println!("🔍 Analyzing dependencies: {}", image_path_str);
+        println!("{}Analyzing dependencies: {}", plain::text("🔍 "), image_path_str);
     }
 
     // Initialize guestfs
     let mut g = Guestfs::new()?;
     g.add_drive_opts(&image_path, true, None)?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
     g.launch()?;
 
     // Inspect OS
@@ -123,10 +127,7 @@ pub fn analyze_dependencies<P: AsRef<Path>>(
     let root = &roots[0];
 
     // Mount filesystems
-    let mountpoints = g.inspect_get_mountpoints(root)?;
-    for (mp, dev) in mountpoints {
-        let _ = g.mount(&dev, &mp);
-    }
+    g.mount_os_ro(root)?;
 
     // Get OS type to determine package manager
     let os_name = g.inspect_get_product_name(root)?;
@@ -172,7 +173,7 @@ pub fn analyze_dependencies<P: AsRef<Path>>(
     let statistics = calculate_statistics(&packages, &dependencies, &circular_dependencies, &conflicts);
 
     if verbose {
-        println!("✅ Dependency analysis complete");
+        println!("{} Dependency analysis complete", plain::text("✅"));
         println!("  Total packages: {}", statistics.total_packages);
         println!("  Total dependencies: {}", statistics.total_dependencies);
         println!("  Circular dependencies: {}", statistics.circular_dependencies);
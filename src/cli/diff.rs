@@ -2,6 +2,7 @@
 //! VM comparison and diff functionality
 
 use super::formatters::InspectionReport;
+use super::plain;
 use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 
@@ -214,8 +215,11 @@ impl InspectionDiff {
             has_changes = true;
             for change in &self.os_changes {
                 println!(
-                    "  {}: {} → {}",
-                    change.field, change.old_value, change.new_value
+                    "  {}: {} {} {}",
+                    change.field,
+                    change.old_value,
+                    plain::text("→"),
+                    change.new_value
                 );
             }
         }
@@ -246,8 +250,11 @@ impl InspectionDiff {
                 println!("  Updated ({}):", self.package_changes.updated.len());
                 for pkg in &self.package_changes.updated {
                     println!(
-                        "    ~ {}: {} → {}",
-                        pkg.name, pkg.old_version, pkg.new_version
+                        "    ~ {}: {} {} {}",
+                        pkg.name,
+                        pkg.old_version,
+                        plain::text("→"),
+                        pkg.new_version
                     );
                 }
             }
@@ -299,8 +306,11 @@ impl InspectionDiff {
             has_changes = true;
             for change in &self.network_changes {
                 println!(
-                    "  {}: {} → {}",
-                    change.field, change.old_value, change.new_value
+                    "  {}: {} {} {}",
+                    change.field,
+                    change.old_value,
+                    plain::text("→"),
+                    change.new_value
                 );
             }
         }
@@ -311,8 +321,11 @@ impl InspectionDiff {
             has_changes = true;
             for change in &self.config_changes {
                 println!(
-                    "  {}: {} → {}",
-                    change.field, change.old_value, change.new_value
+                    "  {}: {} {} {}",
+                    change.field,
+                    change.old_value,
+                    plain::text("→"),
+                    change.new_value
                 );
             }
         }
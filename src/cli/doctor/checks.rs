@@ -0,0 +1,600 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Individual host checks
+
+use super::{CheckStatus, DoctorCheck};
+use std::ffi::CString;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Free space below which a directory check fails
+const FAIL_BELOW_BYTES: u64 = 1024 * 1024 * 1024;
+
+/// Free space below which a directory check warns
+const WARN_BELOW_BYTES: u64 = 5 * 1024 * 1024 * 1024;
+
+/// Host package manager, used to phrase install hints
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PackageManager {
+    Dnf,
+    Apt,
+    Zypper,
+    Pacman,
+    Unknown,
+}
+
+impl PackageManager {
+    /// Detect from host os-release content (`ID` and `ID_LIKE`)
+    pub fn from_os_release(content: &str) -> Self {
+        let ids: Vec<String> = content
+            .lines()
+            .filter_map(|line| {
+                line.strip_prefix("ID=")
+                    .or_else(|| line.strip_prefix("ID_LIKE="))
+            })
+            .flat_map(|value| {
+                value
+                    .trim_matches('"')
+                    .split_whitespace()
+                    .map(str::to_string)
+                    .collect::<Vec<_>>()
+            })
+            .collect();
+        let has = |id: &str| ids.iter().any(|i| i == id);
+
+        if has("fedora") || has("rhel") || has("centos") {
+            Self::Dnf
+        } else if has("debian") || has("ubuntu") {
+            Self::Apt
+        } else if has("suse") || has("opensuse") {
+            Self::Zypper
+        } else if has("arch") {
+            Self::Pacman
+        } else {
+            Self::Unknown
+        }
+    }
+
+    /// Install command for a package
+    pub fn install_hint(&self, package: &str) -> String {
+        match self {
+            Self::Dnf => format!("sudo dnf install {}", package),
+            Self::Apt => format!("sudo apt install {}", package),
+            Self::Zypper => format!("sudo zypper install {}", package),
+            Self::Pacman => format!("sudo pacman -S {}", package),
+            Self::Unknown => format!("install the {} package", package),
+        }
+    }
+}
+
+/// Host facts shared by the checks
+#[derive(Debug, Clone)]
+pub struct HostInfo {
+    pub is_root: bool,
+    pub user: String,
+    pub package_manager: PackageManager,
+    pub has_sudo: bool,
+}
+
+impl HostInfo {
+    pub fn detect() -> Self {
+        let os_release = std::fs::read_to_string("/etc/os-release")
+            .or_else(|_| std::fs::read_to_string("/usr/lib/os-release"))
+            .unwrap_or_default();
+
+        Self {
+            is_root: unsafe { libc::geteuid() } == 0,
+            user: std::env::var("USER").unwrap_or_else(|_| "$USER".to_string()),
+            package_manager: PackageManager::from_os_release(&os_release),
+            has_sudo: find_in_path("sudo").is_some(),
+        }
+    }
+}
+
+/// External tool used by some commands
+struct Tool {
+    /// Binary names, the first found wins
+    binaries: &'static [&'static str],
+    purpose: &'static str,
+    required: bool,
+    /// Package name for dnf/zypper/pacman hosts
+    rpm_package: &'static str,
+    /// Package name for apt hosts
+    deb_package: &'static str,
+}
+
+const TOOLS: &[Tool] = &[
+    Tool {
+        binaries: &["qemu-img"],
+        purpose: "image info and conversion",
+        required: true,
+        rpm_package: "qemu-img",
+        deb_package: "qemu-utils",
+    },
+    Tool {
+        binaries: &["qemu-nbd"],
+        purpose: "attaching qcow2/vmdk images",
+        required: true,
+        rpm_package: "qemu-img",
+        deb_package: "qemu-utils",
+    },
+    Tool {
+        binaries: &["blkid"],
+        purpose: "filesystem detection",
+        required: true,
+        rpm_package: "util-linux",
+        deb_package: "util-linux",
+    },
+    Tool {
+        binaries: &["lvm", "vgchange"],
+        purpose: "LVM volumes",
+        required: false,
+        rpm_package: "lvm2",
+        deb_package: "lvm2",
+    },
+    Tool {
+        binaries: &["thin_check"],
+        purpose: "LVM thin and cache pool checks",
+        required: false,
+        rpm_package: "device-mapper-persistent-data",
+        deb_package: "thin-provisioning-tools",
+    },
+    Tool {
+        binaries: &["cryptsetup"],
+        purpose: "LUKS encrypted volumes",
+        required: false,
+        rpm_package: "cryptsetup",
+        deb_package: "cryptsetup",
+    },
+    Tool {
+        binaries: &["mdadm"],
+        purpose: "software RAID",
+        required: false,
+        rpm_package: "mdadm",
+        deb_package: "mdadm",
+    },
+    Tool {
+        binaries: &["btrfs"],
+        purpose: "Btrfs subvolumes",
+        required: false,
+        rpm_package: "btrfs-progs",
+        deb_package: "btrfs-progs",
+    },
+    Tool {
+        binaries: &["zfs"],
+        purpose: "ZFS pools",
+        required: false,
+        rpm_package: "zfs",
+        deb_package: "zfsutils-linux",
+    },
+    Tool {
+        binaries: &["ntfs-3g"],
+        purpose: "NTFS volumes",
+        required: false,
+        rpm_package: "ntfs-3g",
+        deb_package: "ntfs-3g",
+    },
+    Tool {
+        binaries: &["ldmtool"],
+        purpose: "Windows dynamic disks",
+        required: false,
+        rpm_package: "ldmtool",
+        deb_package: "ldmtool",
+    },
+    Tool {
+        binaries: &["sgdisk"],
+        purpose: "GPT partition editing",
+        required: false,
+        rpm_package: "gdisk",
+        deb_package: "gdisk",
+    },
+    Tool {
+        binaries: &["parted"],
+        purpose: "partition editing",
+        required: false,
+        rpm_package: "parted",
+        deb_package: "parted",
+    },
+    Tool {
+        binaries: &["fusermount3", "fusermount"],
+        purpose: "FUSE mounts",
+        required: false,
+        rpm_package: "fuse3",
+        deb_package: "fuse3",
+    },
+    Tool {
+        binaries: &["sqlite3"],
+        purpose: "dnf/yum history (package-history)",
+        required: false,
+        rpm_package: "sqlite",
+        deb_package: "sqlite3",
+    },
+    Tool {
+        binaries: &["curl"],
+        purpose: "manifest downloads (pristine)",
+        required: false,
+        rpm_package: "curl",
+        deb_package: "curl",
+    },
+];
+
+/// Privilege checks
+pub fn check_privileges(host: &HostInfo) -> Vec<DoctorCheck> {
+    let check = if host.is_root {
+        DoctorCheck::ok("privileges", "root", "running as root")
+    } else if host.has_sudo {
+        DoctorCheck::warn(
+            "privileges",
+            "root",
+            format!(
+                "running as {}; disk operations use sudo and may prompt",
+                host.user
+            ),
+            "run with sudo, or allow passwordless sudo for qemu-nbd, mount and modprobe",
+        )
+    } else {
+        DoctorCheck::fail(
+            "privileges",
+            "root",
+            format!("running as {} and sudo is not installed", host.user),
+            "run guestctl as root",
+        )
+    };
+
+    vec![check]
+}
+
+/// Kernel module checks (nbd, loop, fuse)
+pub fn check_kernel_modules(host: &HostInfo) -> Vec<DoctorCheck> {
+    let modules_package = match host.package_manager {
+        PackageManager::Apt => "linux-modules-extra-$(uname -r)",
+        _ => "kernel-modules",
+    };
+
+    let mut results = Vec::new();
+
+    // nbd attaches non-raw images; guestctl loads it on demand via sudo
+    results.push(if module_loaded("nbd") {
+        let devices = count_devices("nbd");
+        if devices == 0 {
+            DoctorCheck::warn(
+                "kernel",
+                "nbd",
+                "module loaded but no /dev/nbd* devices",
+                "sudo modprobe -r nbd && sudo modprobe nbd max_part=16",
+            )
+        } else {
+            DoctorCheck::ok("kernel", "nbd", format!("loaded, {} devices", devices))
+        }
+    } else if module_available("nbd") {
+        DoctorCheck::warn(
+            "kernel",
+            "nbd",
+            "module available but not loaded (guestctl loads it on first use)",
+            "sudo modprobe nbd max_part=16",
+        )
+    } else {
+        DoctorCheck::fail(
+            "kernel",
+            "nbd",
+            "module not available; qcow2, vmdk and vdi images cannot be attached",
+            host.package_manager.install_hint(modules_package),
+        )
+    });
+
+    results.push(
+        if Path::new("/dev/loop-control").exists() || module_loaded("loop") {
+            DoctorCheck::ok("kernel", "loop", "loop devices available")
+        } else if module_available("loop") {
+            DoctorCheck::warn("kernel", "loop", "module not loaded", "sudo modprobe loop")
+        } else {
+            DoctorCheck::fail(
+                "kernel",
+                "loop",
+                "loop devices not available; raw images and ISOs cannot be attached",
+                host.package_manager.install_hint(modules_package),
+            )
+        },
+    );
+
+    results.push(
+        if Path::new("/dev/fuse").exists() || module_loaded("fuse") {
+            DoctorCheck::ok("kernel", "fuse", "FUSE available")
+        } else if module_available("fuse") {
+            DoctorCheck::warn("kernel", "fuse", "module not loaded", "sudo modprobe fuse")
+        } else {
+            DoctorCheck::warn(
+                "kernel",
+                "fuse",
+                "FUSE not available; FUSE mounts will not work",
+                host.package_manager.install_hint(modules_package),
+            )
+        },
+    );
+
+    results
+}
+
+/// Device node permission checks
+pub fn check_devices(host: &HostInfo) -> Vec<DoctorCheck> {
+    let mut results = Vec::new();
+
+    let kvm = Path::new("/dev/kvm");
+    results.push(if !kvm.exists() {
+        DoctorCheck::warn(
+            "devices",
+            "/dev/kvm",
+            "not present; QEMU falls back to slow emulation",
+            "enable VT-x/AMD-V in firmware, then sudo modprobe kvm_intel (or kvm_amd)",
+        )
+    } else if can_read_write(kvm) {
+        DoctorCheck::ok("devices", "/dev/kvm", "read/write access")
+    } else {
+        DoctorCheck::warn(
+            "devices",
+            "/dev/kvm",
+            "no read/write access",
+            format!("sudo usermod -aG kvm {} (then log in again)", host.user),
+        )
+    });
+
+    let fuse = Path::new("/dev/fuse");
+    if fuse.exists() {
+        results.push(if can_read_write(fuse) {
+            DoctorCheck::ok("devices", "/dev/fuse", "read/write access")
+        } else {
+            DoctorCheck::warn(
+                "devices",
+                "/dev/fuse",
+                "no read/write access",
+                "sudo chmod 0666 /dev/fuse",
+            )
+        });
+    }
+
+    results
+}
+
+/// Free space and writability of the cache and temporary directories
+pub fn check_free_space() -> Vec<DoctorCheck> {
+    let cache_dir = std::env::var("GUESTCTL_CACHE_DIR")
+        .map(PathBuf::from)
+        .or_else(|_| {
+            std::env::var("HOME").map(|home| PathBuf::from(home).join(".cache").join("guestctl"))
+        })
+        .unwrap_or_else(|_| PathBuf::from("/var/cache/guestctl"));
+
+    [("cache", cache_dir), ("tmp", std::env::temp_dir())]
+        .into_iter()
+        .map(|(name, dir)| check_directory(name, &dir))
+        .collect()
+}
+
+fn check_directory(name: &str, dir: &Path) -> DoctorCheck {
+    let label = format!("{} ({})", name, dir.display());
+    let Some(existing) = nearest_existing(dir) else {
+        return DoctorCheck::fail(
+            "storage",
+            &label,
+            "no existing parent directory",
+            "create the directory",
+        );
+    };
+
+    if !can_write(&existing) {
+        return DoctorCheck::fail(
+            "storage",
+            &label,
+            format!("{} is not writable", existing.display()),
+            match name {
+                "cache" => "pass --cache-dir with a writable directory",
+                _ => "set TMPDIR to a writable directory",
+            },
+        );
+    }
+
+    let Some(free) = free_bytes(&existing) else {
+        return DoctorCheck::warn(
+            "storage",
+            &label,
+            "could not read free space",
+            "check the filesystem with df -h",
+        );
+    };
+
+    let detail = format!("{} free", human_bytes(free));
+    let fix = match name {
+        "cache" => "free up space, or pass --cache-dir on a larger filesystem",
+        _ => "free up space, or set TMPDIR to a larger filesystem",
+    };
+    match classify_free_space(free) {
+        CheckStatus::Ok => DoctorCheck::ok("storage", &label, detail),
+        CheckStatus::Warn => DoctorCheck::warn("storage", &label, detail, fix),
+        CheckStatus::Fail => DoctorCheck::fail("storage", &label, detail, fix),
+    }
+}
+
+/// Required and optional external tools
+pub fn check_tools(host: &HostInfo) -> Vec<DoctorCheck> {
+    TOOLS
+        .iter()
+        .map(|tool| {
+            let name = tool.binaries[0];
+            match tool.binaries.iter().find_map(|b| find_in_path(b)) {
+                Some(path) => DoctorCheck::ok(
+                    "tools",
+                    name,
+                    format!("{} ({})", path.display(), tool.purpose),
+                ),
+                None => {
+                    let package = match host.package_manager {
+                        PackageManager::Apt => tool.deb_package,
+                        _ => tool.rpm_package,
+                    };
+                    let detail = format!("not found; needed for {}", tool.purpose);
+                    let fix = host.package_manager.install_hint(package);
+                    if tool.required {
+                        DoctorCheck::fail("tools", name, detail, fix)
+                    } else {
+                        DoctorCheck::warn("tools", name, detail, fix)
+                    }
+                }
+            }
+        })
+        .collect()
+}
+
+/// Status for a directory with the given free space
+pub fn classify_free_space(free: u64) -> CheckStatus {
+    if free < FAIL_BELOW_BYTES {
+        CheckStatus::Fail
+    } else if free < WARN_BELOW_BYTES {
+        CheckStatus::Warn
+    } else {
+        CheckStatus::Ok
+    }
+}
+
+/// Find an executable on `PATH`
+pub fn find_in_path(binary: &str) -> Option<PathBuf> {
+    let path = std::env::var_os("PATH")?;
+    find_in_dirs(binary, std::env::split_paths(&path))
+}
+
+fn find_in_dirs(binary: &str, dirs: impl Iterator<Item = PathBuf>) -> Option<PathBuf> {
+    use std::os::unix::fs::PermissionsExt;
+
+    dirs.map(|dir| dir.join(binary)).find(|candidate| {
+        std::fs::metadata(candidate)
+            .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false)
+    })
+}
+
+/// Closest ancestor of `path` (or `path` itself) that exists
+fn nearest_existing(path: &Path) -> Option<PathBuf> {
+    path.ancestors().find(|p| p.exists()).map(Path::to_path_buf)
+}
+
+fn module_loaded(name: &str) -> bool {
+    Path::new("/sys/module").join(name).exists()
+}
+
+/// Whether the module can be loaded (or is built in)
+fn module_available(name: &str) -> bool {
+    Command::new("modinfo")
+        .arg("-n")
+        .arg(name)
+        .output()
+        .map(|o| o.status.success())
+        .unwrap_or(false)
+}
+
+fn count_devices(prefix: &str) -> usize {
+    std::fs::read_dir("/dev")
+        .map(|entries| {
+            entries
+                .flatten()
+                .filter(|e| {
+                    let name = e.file_name().to_string_lossy().to_string();
+                    name.strip_prefix(prefix)
+                        .map(|rest| !rest.is_empty() && rest.chars().all(|c| c.is_ascii_digit()))
+                        .unwrap_or(false)
+                })
+                .count()
+        })
+        .unwrap_or(0)
+}
+
+fn access(path: &Path, mode: libc::c_int) -> bool {
+    match CString::new(path.to_string_lossy().as_bytes()) {
+        Ok(c_path) => unsafe { libc::access(c_path.as_ptr(), mode) == 0 },
+        Err(_) => false,
+    }
+}
+
+fn can_read_write(path: &Path) -> bool {
+    access(path, libc::R_OK | libc::W_OK)
+}
+
+fn can_write(path: &Path) -> bool {
+    access(path, libc::W_OK)
+}
+
+fn free_bytes(path: &Path) -> Option<u64> {
+    let c_path = CString::new(path.to_string_lossy().as_bytes()).ok()?;
+    let mut stat: libc::statvfs = unsafe { std::mem::zeroed() };
+    if unsafe { libc::statvfs(c_path.as_ptr(), &mut stat) } != 0 {
+        return None;
+    }
+    Some(stat.f_bavail as u64 * stat.f_frsize as u64)
+}
+
+fn human_bytes(bytes: u64) -> String {
+    const GIB: f64 = 1024.0 * 1024.0 * 1024.0;
+    const MIB: f64 = 1024.0 * 1024.0;
+    if bytes as f64 >= GIB {
+        format!("{:.1} GiB", bytes as f64 / GIB)
+    } else {
+        format!("{:.0} MiB", bytes as f64 / MIB)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_package_manager_detection() {
+        let fedora = "NAME=\"Fedora Linux\"\nID=fedora\nVERSION_ID=40\n";
+        assert_eq!(PackageManager::from_os_release(fedora), PackageManager::Dnf);
+
+        let mint = "ID=linuxmint\nID_LIKE=\"ubuntu debian\"\n";
+        assert_eq!(PackageManager::from_os_release(mint), PackageManager::Apt);
+
+        let alma = "ID=\"almalinux\"\nID_LIKE=\"rhel centos fedora\"\n";
+        assert_eq!(PackageManager::from_os_release(alma), PackageManager::Dnf);
+
+        assert_eq!(PackageManager::from_os_release(""), PackageManager::Unknown);
+        assert_eq!(
+            PackageManager::Apt.install_hint("qemu-utils"),
+            "sudo apt install qemu-utils"
+        );
+    }
+
+    #[test]
+    fn test_classify_free_space() {
+        assert_eq!(classify_free_space(512 * 1024 * 1024), CheckStatus::Fail);
+        assert_eq!(
+            classify_free_space(2 * 1024 * 1024 * 1024),
+            CheckStatus::Warn
+        );
+        assert_eq!(
+            classify_free_space(20 * 1024 * 1024 * 1024),
+            CheckStatus::Ok
+        );
+    }
+
+    #[test]
+    fn test_find_in_dirs() {
+        use std::os::unix::fs::PermissionsExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let tool = dir.path().join("qemu-img");
+        std::fs::write(&tool, "#!/bin/sh\n").unwrap();
+        std::fs::set_permissions(&tool, std::fs::Permissions::from_mode(0o755)).unwrap();
+        let data = dir.path().join("notes");
+        std::fs::write(&data, "").unwrap();
+
+        let dirs = || vec![PathBuf::from("/nonexistent"), dir.path().to_path_buf()].into_iter();
+        assert_eq!(find_in_dirs("qemu-img", dirs()), Some(tool));
+        assert_eq!(find_in_dirs("notes", dirs()), None);
+        assert_eq!(find_in_dirs("qemu-nbd", dirs()), None);
+    }
+
+    #[test]
+    fn test_nearest_existing() {
+        let dir = tempfile::tempdir().unwrap();
+        let missing = dir.path().join("a").join("b");
+        assert_eq!(nearest_existing(&missing), Some(dir.path().to_path_buf()));
+    }
+}
@@ -0,0 +1,120 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Host prerequisites checks
+//!
+//! Most first-run failures come from the host environment (missing kernel
+//! modules, tools or device permissions) rather than the image. These
+//! checks find them up front and suggest a fix for each.
+
+pub mod checks;
+pub mod reporter;
+
+use serde::{Deserialize, Serialize};
+
+/// Outcome of a single check
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CheckStatus {
+    Ok,
+    /// Optional feature unavailable or degraded
+    Warn,
+    /// Required for most commands
+    Fail,
+}
+
+impl CheckStatus {
+    pub fn emoji(&self) -> &str {
+        match self {
+            Self::Ok => "✅",
+            Self::Warn => "⚠️",
+            Self::Fail => "❌",
+        }
+    }
+}
+
+/// Result of a single host check
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DoctorCheck {
+    /// Group shown in the report (privileges, kernel, devices, ...)
+    pub category: String,
+    pub name: String,
+    pub status: CheckStatus,
+    pub detail: String,
+    /// Suggested command or action when the check is not ok
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub fix: Option<String>,
+}
+
+impl DoctorCheck {
+    pub fn ok(category: &str, name: &str, detail: impl Into<String>) -> Self {
+        Self {
+            category: category.to_string(),
+            name: name.to_string(),
+            status: CheckStatus::Ok,
+            detail: detail.into(),
+            fix: None,
+        }
+    }
+
+    pub fn warn(
+        category: &str,
+        name: &str,
+        detail: impl Into<String>,
+        fix: impl Into<String>,
+    ) -> Self {
+        Self {
+            status: CheckStatus::Warn,
+            fix: Some(fix.into()),
+            ..Self::ok(category, name, detail)
+        }
+    }
+
+    pub fn fail(
+        category: &str,
+        name: &str,
+        detail: impl Into<String>,
+        fix: impl Into<String>,
+    ) -> Self {
+        Self {
+            status: CheckStatus::Fail,
+            fix: Some(fix.into()),
+            ..Self::ok(category, name, detail)
+        }
+    }
+}
+
+/// Host prerequisites report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct DoctorReport {
+    pub checked_at: String,
+    pub checks: Vec<DoctorCheck>,
+    pub passed: usize,
+    pub warnings: usize,
+    pub failures: usize,
+}
+
+impl DoctorReport {
+    pub fn new(checks: Vec<DoctorCheck>) -> Self {
+        let count = |status| checks.iter().filter(|c| c.status == status).count();
+        Self {
+            checked_at: chrono::Utc::now().to_rfc3339(),
+            passed: count(CheckStatus::Ok),
+            warnings: count(CheckStatus::Warn),
+            failures: count(CheckStatus::Fail),
+            checks,
+        }
+    }
+}
+
+/// Run all host checks
+pub fn run_doctor() -> DoctorReport {
+    let host = checks::HostInfo::detect();
+
+    let mut results = Vec::new();
+    results.extend(checks::check_privileges(&host));
+    results.extend(checks::check_kernel_modules(&host));
+    results.extend(checks::check_devices(&host));
+    results.extend(checks::check_free_space());
+    results.extend(checks::check_tools(&host));
+
+    DoctorReport::new(results)
+}
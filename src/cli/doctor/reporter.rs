@@ -0,0 +1,65 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Doctor report formatting
+
+use super::{CheckStatus, DoctorReport};
+use crate::cli::i18n::{tr, tr_args};
+use crate::cli::output::display_width;
+
+/// Format doctor report as text
+///
+/// Passing checks are listed only when `verbose` is set.
+pub fn format_report(report: &DoctorReport, verbose: bool) -> String {
+    let mut output = String::new();
+
+    let title = tr("doctor-title");
+    output.push_str(&format!("{}\n", title));
+    output.push_str(&format!("{}\n\n", "=".repeat(display_width(&title))));
+
+    let mut category = "";
+    for check in &report.checks {
+        if check.status == CheckStatus::Ok && !verbose {
+            continue;
+        }
+        if check.category != category {
+            if !category.is_empty() {
+                output.push('\n');
+            }
+            category = check.category.as_str();
+            output.push_str(&format!("{}\n", category));
+            output.push_str(&format!("{}\n", "-".repeat(category.len())));
+        }
+
+        output.push_str(&format!(
+            "{} {}: {}\n",
+            check.status.emoji(),
+            check.name,
+            check.detail
+        ));
+        if let Some(ref fix) = check.fix {
+            output.push_str(&format!(
+                "   └─ {}\n",
+                tr_args("doctor-fix", &[("fix", fix.as_str().into())])
+            ));
+        }
+    }
+    if !category.is_empty() {
+        output.push('\n');
+    }
+
+    let summary = tr_args(
+        "doctor-summary",
+        &[
+            ("passed", report.passed.into()),
+            ("warnings", report.warnings.into()),
+            ("failures", report.failures.into()),
+        ],
+    );
+    output.push_str(&format!("{}\n", summary));
+    if report.failures == 0 && report.warnings == 0 {
+        output.push_str(&format!("{}\n", tr("doctor-ready")));
+    } else if report.failures == 0 {
+        output.push_str(&format!("{}\n", tr("doctor-usable")));
+    }
+
+    output
+}
@@ -93,9 +93,9 @@ pub mod errors {
         // Find similar commands (simple prefix matching)
         let similar: Vec<String> = available
             .iter()
-            .filter(|&&cmd| {
-                command.starts_with(&cmd[..1.min(cmd.len())])
-                    || cmd.starts_with(&command[..1.min(command.len())])
+            .filter(|&&cmd| match (command.chars().next(), cmd.chars().next()) {
+                (Some(a), Some(b)) => a == b,
+                _ => true,
             })
             .take(3)
             .map(|&s| s.to_string())
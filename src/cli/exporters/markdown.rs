@@ -581,6 +581,7 @@ mod tests {
     fn test_generate_badges() {
         let mut report = InspectionReport {
             image_path: None,
+            os_instances: None,
             os: OsInfo {
                 root: "/".to_string(),
                 os_type: Some("linux".to_string()),
@@ -616,6 +617,7 @@ mod tests {
     fn test_generate_architecture_diagram() {
         let report = InspectionReport {
             image_path: None,
+            os_instances: None,
             os: OsInfo {
                 root: "/".to_string(),
                 os_type: Some("linux".to_string()),
@@ -654,6 +656,7 @@ mod tests {
     fn test_generate_markdown_report_basic() {
         let report = InspectionReport {
             image_path: None,
+            os_instances: None,
             os: OsInfo {
                 root: "/".to_string(),
                 os_type: Some("linux".to_string()),
@@ -693,6 +696,7 @@ mod tests {
     fn test_generate_markdown_without_diagrams() {
         let report = InspectionReport {
             image_path: None,
+            os_instances: None,
             os: OsInfo {
                 root: "/".to_string(),
                 os_type: Some("linux".to_string()),
@@ -0,0 +1,634 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Fleet report: aggregate batch run results across many images
+
+pub mod reporter;
+
+use anyhow::{Context, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+use crate::cli::catalog::Catalog;
+use crate::cli::inventory::Inventory;
+use crate::cli::validate::ValidationReport;
+
+/// Kind of result file found in a run directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ResultKind {
+    Validation,
+    Score,
+    Inventory,
+}
+
+/// `guestctl score --export <file>.json` output
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScoreResult {
+    pub image_path: String,
+    #[serde(default)]
+    pub generated_at: Option<String>,
+    pub overall_score: u32,
+    pub grade: String,
+    #[serde(default)]
+    pub dimensions: BTreeMap<String, u32>,
+}
+
+/// Policy validation outcome for one image
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationEntry {
+    pub policy: String,
+    pub compliance_score: f64,
+    pub passed: usize,
+    pub failed: usize,
+    pub warnings: usize,
+}
+
+/// Risk score for one image
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ScoreEntry {
+    pub overall: u32,
+    pub grade: String,
+}
+
+/// CVE counts by severity
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CveCounts {
+    pub critical: usize,
+    pub high: usize,
+    pub medium: usize,
+    pub low: usize,
+}
+
+impl CveCounts {
+    /// Counts from an inventory's severity histogram
+    pub fn from_severities<'a>(
+        severities: impl IntoIterator<Item = (&'a String, &'a usize)>,
+    ) -> Self {
+        let mut counts = Self::default();
+        for (severity, count) in severities {
+            match severity.to_lowercase().as_str() {
+                "critical" => counts.critical += count,
+                "high" => counts.high += count,
+                "medium" | "moderate" => counts.medium += count,
+                _ => counts.low += count,
+            }
+        }
+        counts
+    }
+
+    pub fn total(&self) -> usize {
+        self.critical + self.high + self.medium + self.low
+    }
+
+    fn add(&mut self, other: &CveCounts) {
+        self.critical += other.critical;
+        self.high += other.high;
+        self.medium += other.medium;
+        self.low += other.low;
+    }
+}
+
+/// All results collected for one image in a run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImageRecord {
+    pub image: String,
+    /// Catalog id, the image's identity across runs, if it is cataloged
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub catalog_id: Option<String>,
+    /// Catalog reference (`name:tag`)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    pub os: Option<String>,
+    pub validation: Option<ValidationEntry>,
+    pub score: Option<ScoreEntry>,
+    pub cves: Option<CveCounts>,
+    /// Result files this record was built from
+    pub sources: Vec<String>,
+}
+
+impl ImageRecord {
+    fn new(image: &str) -> Self {
+        Self {
+            image: image.to_string(),
+            catalog_id: None,
+            name: None,
+            os: None,
+            validation: None,
+            score: None,
+            cves: None,
+            sources: Vec::new(),
+        }
+    }
+
+    /// Catalog reference if known, else the image path
+    pub fn display_name(&self) -> &str {
+        self.name.as_deref().unwrap_or(&self.image)
+    }
+}
+
+/// Aggregate figures for a run
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FleetSummary {
+    pub images: usize,
+    pub validated: usize,
+    pub failing_validation: usize,
+    pub average_compliance: Option<f64>,
+    pub scored: usize,
+    pub average_score: Option<f64>,
+    pub scanned: usize,
+    pub cves: CveCounts,
+}
+
+/// Results of one batch run
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FleetRun {
+    /// Run name (directory name)
+    pub label: String,
+    pub path: String,
+    /// Latest timestamp found in the run's results
+    pub timestamp: Option<String>,
+    pub images: Vec<ImageRecord>,
+    pub summary: FleetSummary,
+    pub files_read: usize,
+    pub warnings: Vec<String>,
+}
+
+/// One point of a trend chart
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TrendPoint {
+    pub label: String,
+    pub timestamp: Option<String>,
+    pub images: usize,
+    pub average_compliance: Option<f64>,
+    pub average_score: Option<f64>,
+    pub cves: CveCounts,
+}
+
+/// Fleet dashboard data
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FleetReport {
+    pub generated_at: String,
+    pub current: FleetRun,
+    /// Oldest run first, ending with the current run; empty without history
+    pub trend: Vec<TrendPoint>,
+}
+
+/// Read every result file under `dir` into a run
+///
+/// With a catalog, results for cataloged images are merged by catalog id
+/// rather than by path.
+pub fn load_run(dir: &Path, catalog: Option<&Catalog>) -> Result<FleetRun> {
+    if !dir.is_dir() {
+        anyhow::bail!("Results directory not found: {}", dir.display());
+    }
+
+    let mut files = Vec::new();
+    collect_json_files(dir, &mut files)?;
+    files.sort();
+
+    let mut records: BTreeMap<String, ImageRecord> = BTreeMap::new();
+    let mut warnings = Vec::new();
+    let mut timestamp: Option<String> = None;
+    let mut files_read = 0;
+
+    for file in &files {
+        let relative = file.strip_prefix(dir).unwrap_or(file).display().to_string();
+
+        let text = match std::fs::read_to_string(file) {
+            Ok(text) => text,
+            Err(e) => {
+                warnings.push(format!("{}: {}", relative, e));
+                continue;
+            }
+        };
+        let value: serde_json::Value = match serde_json::from_str(&text) {
+            Ok(value) => value,
+            Err(e) => {
+                warnings.push(format!("{}: not valid JSON ({})", relative, e));
+                continue;
+            }
+        };
+
+        let Some(kind) = detect_kind(&value) else {
+            warnings.push(format!(
+                "{}: not a validate, score or inventory result",
+                relative
+            ));
+            continue;
+        };
+
+        match apply_result(kind, value, &mut records, &relative, catalog) {
+            Ok(Some(ts)) => {
+                if timestamp.as_deref() < Some(ts.as_str()) {
+                    timestamp = Some(ts);
+                }
+                files_read += 1;
+            }
+            Ok(None) => files_read += 1,
+            Err(e) => warnings.push(format!("{}: {}", relative, e)),
+        }
+    }
+
+    let images: Vec<ImageRecord> = records.into_values().collect();
+    let summary = summarize(&images);
+
+    Ok(FleetRun {
+        label: dir
+            .file_name()
+            .map(|n| n.to_string_lossy().to_string())
+            .unwrap_or_else(|| dir.display().to_string()),
+        path: dir.display().to_string(),
+        timestamp,
+        images,
+        summary,
+        files_read,
+        warnings,
+    })
+}
+
+/// Build the fleet report for `dir`, with trends over earlier `history` runs
+pub fn build_report(
+    dir: &Path,
+    history: &[&Path],
+    catalog: Option<&Catalog>,
+) -> Result<FleetReport> {
+    let current = load_run(dir, catalog)?;
+
+    let mut trend = Vec::new();
+    if !history.is_empty() {
+        let mut runs = Vec::new();
+        for path in history {
+            runs.push(load_run(path, catalog)?);
+        }
+        // Runs without timestamps keep their command line order, ahead of dated ones
+        runs.sort_by(|a, b| a.timestamp.cmp(&b.timestamp));
+        trend = runs.iter().map(trend_point).collect();
+        trend.push(trend_point(&current));
+    }
+
+    Ok(FleetReport {
+        generated_at: chrono::Utc::now().to_rfc3339(),
+        current,
+        trend,
+    })
+}
+
+fn trend_point(run: &FleetRun) -> TrendPoint {
+    TrendPoint {
+        label: run.label.clone(),
+        timestamp: run.timestamp.clone(),
+        images: run.summary.images,
+        average_compliance: run.summary.average_compliance,
+        average_score: run.summary.average_score,
+        cves: run.summary.cves,
+    }
+}
+
+fn collect_json_files(dir: &Path, files: &mut Vec<std::path::PathBuf>) -> Result<()> {
+    let entries =
+        std::fs::read_dir(dir).with_context(|| format!("Failed to read {}", dir.display()))?;
+    for entry in entries.flatten() {
+        let path = entry.path();
+        if path.is_dir() {
+            collect_json_files(&path, files)?;
+        } else if path.extension().and_then(|e| e.to_str()) == Some("json") {
+            files.push(path);
+        }
+    }
+    Ok(())
+}
+
+/// Recognise a result file by its fields
+pub fn detect_kind(value: &serde_json::Value) -> Option<ResultKind> {
+    if value.get("policy_name").is_some() && value.pointer("/summary/compliance_score").is_some() {
+        Some(ResultKind::Validation)
+    } else if value.get("overall_score").is_some() {
+        Some(ResultKind::Score)
+    } else if value.pointer("/statistics/vulnerabilities").is_some() {
+        Some(ResultKind::Inventory)
+    } else {
+        None
+    }
+}
+
+/// Merge one result into the per-image records, returning its timestamp
+fn apply_result(
+    kind: ResultKind,
+    value: serde_json::Value,
+    records: &mut BTreeMap<String, ImageRecord>,
+    source: &str,
+    catalog: Option<&Catalog>,
+) -> Result<Option<String>> {
+    let timestamp = match kind {
+        ResultKind::Validation => {
+            let report: ValidationReport = serde_json::from_value(value)?;
+            let record = record_for(records, &report.image_path, catalog);
+            record.validation = Some(ValidationEntry {
+                policy: report.policy_name,
+                compliance_score: report.summary.compliance_score,
+                passed: report.summary.passed,
+                failed: report.summary.failed,
+                warnings: report.summary.warnings,
+            });
+            record.sources.push(source.to_string());
+            Some(report.timestamp)
+        }
+        ResultKind::Score => {
+            let result: ScoreResult = serde_json::from_value(value)?;
+            let record = record_for(records, &result.image_path, catalog);
+            record.score = Some(ScoreEntry {
+                overall: result.overall_score,
+                grade: result.grade,
+            });
+            record.sources.push(source.to_string());
+            result.generated_at
+        }
+        ResultKind::Inventory => {
+            let inventory: Inventory = serde_json::from_value(value)?;
+            let record = record_for(records, &inventory.image_path, catalog);
+            record.os = Some(format!("{} {}", inventory.os_name, inventory.os_version));
+            record.cves = Some(CveCounts::from_severities(
+                &inventory.statistics.vulnerabilities,
+            ));
+            record.sources.push(source.to_string());
+            Some(inventory.scanned_at)
+        }
+    };
+
+    Ok(timestamp)
+}
+
+/// Record for `image_path`, keyed by catalog id when the image is cataloged
+fn record_for<'a>(
+    records: &'a mut BTreeMap<String, ImageRecord>,
+    image_path: &str,
+    catalog: Option<&Catalog>,
+) -> &'a mut ImageRecord {
+    let entry = catalog.and_then(|catalog| catalog.find_by_location(Path::new(image_path)));
+    let key = entry.map_or_else(|| image_path.to_string(), |entry| entry.id.clone());
+
+    records.entry(key).or_insert_with(|| {
+        let mut record = ImageRecord::new(image_path);
+        if let Some(entry) = entry {
+            record.catalog_id = Some(entry.id.clone());
+            record.name = Some(entry.reference());
+        }
+        record
+    })
+}
+
+/// Compute run-wide figures
+pub fn summarize(images: &[ImageRecord]) -> FleetSummary {
+    let mut summary = FleetSummary {
+        images: images.len(),
+        ..Default::default()
+    };
+
+    let mut compliance_total = 0.0;
+    let mut score_total = 0u64;
+    for image in images {
+        if let Some(validation) = &image.validation {
+            summary.validated += 1;
+            compliance_total += validation.compliance_score;
+            if validation.failed > 0 {
+                summary.failing_validation += 1;
+            }
+        }
+        if let Some(score) = &image.score {
+            summary.scored += 1;
+            score_total += u64::from(score.overall);
+        }
+        if let Some(cves) = &image.cves {
+            summary.scanned += 1;
+            summary.cves.add(cves);
+        }
+    }
+
+    if summary.validated > 0 {
+        summary.average_compliance = Some(compliance_total / summary.validated as f64);
+    }
+    if summary.scored > 0 {
+        summary.average_score = Some(score_total as f64 / summary.scored as f64);
+    }
+
+    summary
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashMap;
+
+    fn write(dir: &Path, name: &str, value: serde_json::Value) {
+        std::fs::write(dir.join(name), serde_json::to_string(&value).unwrap()).unwrap();
+    }
+
+    fn validation(image: &str, score: f64, failed: usize, timestamp: &str) -> serde_json::Value {
+        serde_json::json!({
+            "image_path": image,
+            "policy_name": "cis-ubuntu",
+            "timestamp": timestamp,
+            "results": [],
+            "summary": {
+                "total_rules": 10,
+                "passed": 10 - failed,
+                "failed": failed,
+                "warnings": 0,
+                "skipped": 0,
+                "errors": 0,
+                "compliance_score": score
+            }
+        })
+    }
+
+    fn inventory(image: &str, vulnerabilities: serde_json::Value) -> serde_json::Value {
+        serde_json::json!({
+            "image_path": image,
+            "scanned_at": "2026-01-02T00:00:00Z",
+            "os_name": "ubuntu",
+            "os_version": "22.04",
+            "architecture": "x86_64",
+            "packages": [],
+            "statistics": {
+                "total_packages": 0,
+                "total_size": 0,
+                "vulnerabilities": vulnerabilities,
+                "licenses": {}
+            }
+        })
+    }
+
+    #[test]
+    fn test_detect_kind() {
+        assert_eq!(
+            detect_kind(&validation("a.qcow2", 90.0, 1, "2026-01-01T00:00:00Z")),
+            Some(ResultKind::Validation)
+        );
+        assert_eq!(
+            detect_kind(&serde_json::json!({"image_path": "a", "overall_score": 80, "grade": "A"})),
+            Some(ResultKind::Score)
+        );
+        assert_eq!(
+            detect_kind(&inventory("a.qcow2", serde_json::json!({}))),
+            Some(ResultKind::Inventory)
+        );
+        assert_eq!(detect_kind(&serde_json::json!({"job_id": "x"})), None);
+    }
+
+    #[test]
+    fn test_cve_counts_from_severities() {
+        let mut severities = HashMap::new();
+        severities.insert("CRITICAL".to_string(), 2);
+        severities.insert("High".to_string(), 3);
+        severities.insert("moderate".to_string(), 1);
+        severities.insert("low".to_string(), 4);
+        let counts = CveCounts::from_severities(&severities);
+        assert_eq!(
+            counts,
+            CveCounts {
+                critical: 2,
+                high: 3,
+                medium: 1,
+                low: 4
+            }
+        );
+        assert_eq!(counts.total(), 10);
+    }
+
+    #[test]
+    fn test_load_run_merges_results_per_image() {
+        let dir = tempfile::tempdir().unwrap();
+        let nested = dir.path().join("web");
+        std::fs::create_dir(&nested).unwrap();
+
+        write(
+            dir.path(),
+            "db-validate.json",
+            validation("/images/db.qcow2", 80.0, 2, "2026-01-01T00:00:00Z"),
+        );
+        write(
+            &nested,
+            "web-validate.json",
+            validation("/images/web.qcow2", 100.0, 0, "2026-01-03T00:00:00Z"),
+        );
+        write(
+            dir.path(),
+            "db-score.json",
+            serde_json::json!({"image_path": "/images/db.qcow2", "overall_score": 72, "grade": "B"}),
+        );
+        write(
+            dir.path(),
+            "db-inventory.json",
+            inventory(
+                "/images/db.qcow2",
+                serde_json::json!({"critical": 1, "high": 2}),
+            ),
+        );
+        write(dir.path(), "other.json", serde_json::json!({"job_id": "x"}));
+        std::fs::write(dir.path().join("broken.json"), "{").unwrap();
+        std::fs::write(dir.path().join("notes.txt"), "ignored").unwrap();
+
+        let run = load_run(dir.path(), None).unwrap();
+        assert_eq!(run.files_read, 4);
+        assert_eq!(run.warnings.len(), 2);
+        assert_eq!(run.timestamp.as_deref(), Some("2026-01-03T00:00:00Z"));
+        assert_eq!(run.images.len(), 2);
+
+        let db = &run.images[0];
+        assert_eq!(db.image, "/images/db.qcow2");
+        assert_eq!(db.os.as_deref(), Some("ubuntu 22.04"));
+        assert_eq!(db.score.as_ref().unwrap().overall, 72);
+        assert_eq!(db.cves.unwrap().total(), 3);
+        assert_eq!(db.sources.len(), 3);
+
+        let summary = &run.summary;
+        assert_eq!(summary.validated, 2);
+        assert_eq!(summary.failing_validation, 1);
+        assert_eq!(summary.average_compliance, Some(90.0));
+        assert_eq!(summary.average_score, Some(72.0));
+        assert_eq!(summary.cves.critical, 1);
+    }
+
+    #[test]
+    fn test_load_run_uses_catalog_identity() {
+        use crate::cli::catalog::CatalogEntry;
+
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().join("web.qcow2");
+        std::fs::write(&image, "").unwrap();
+        let results = dir.path().join("results");
+        std::fs::create_dir(&results).unwrap();
+
+        // The same image, once by absolute path and once through a symlink
+        let link = dir.path().join("current.qcow2");
+        std::os::unix::fs::symlink(&image, &link).unwrap();
+        write(
+            &results,
+            "validate.json",
+            validation(
+                &image.display().to_string(),
+                90.0,
+                1,
+                "2026-01-01T00:00:00Z",
+            ),
+        );
+        write(
+            &results,
+            "score.json",
+            serde_json::json!({"image_path": link.display().to_string(), "overall_score": 80, "grade": "B"}),
+        );
+
+        let mut catalog = Catalog::default();
+        let id = catalog
+            .add(
+                CatalogEntry::new("web", image.display().to_string())
+                    .with_tags(vec!["prod".to_string()]),
+            )
+            .unwrap()
+            .id
+            .clone();
+
+        let run = load_run(&results, Some(&catalog)).unwrap();
+        assert_eq!(run.images.len(), 1);
+        assert_eq!(run.images[0].catalog_id.as_deref(), Some(id.as_str()));
+        assert_eq!(run.images[0].display_name(), "web:prod");
+        assert!(run.images[0].validation.is_some());
+        assert!(run.images[0].score.is_some());
+
+        let run = load_run(&results, None).unwrap();
+        assert_eq!(run.images.len(), 2);
+    }
+
+    #[test]
+    fn test_build_report_orders_history() {
+        let current = tempfile::tempdir().unwrap();
+        let older = tempfile::tempdir().unwrap();
+        let oldest = tempfile::tempdir().unwrap();
+
+        write(
+            current.path(),
+            "a.json",
+            validation("a.qcow2", 95.0, 0, "2026-03-01T00:00:00Z"),
+        );
+        write(
+            older.path(),
+            "a.json",
+            validation("a.qcow2", 85.0, 1, "2026-02-01T00:00:00Z"),
+        );
+        write(
+            oldest.path(),
+            "a.json",
+            validation("a.qcow2", 70.0, 3, "2026-01-01T00:00:00Z"),
+        );
+
+        let report = build_report(current.path(), &[older.path(), oldest.path()], None).unwrap();
+        let scores: Vec<_> = report
+            .trend
+            .iter()
+            .map(|p| p.average_compliance.unwrap())
+            .collect();
+        assert_eq!(scores, vec![70.0, 85.0, 95.0]);
+
+        let report = build_report(current.path(), &[], None).unwrap();
+        assert!(report.trend.is_empty());
+    }
+}
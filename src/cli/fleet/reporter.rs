@@ -0,0 +1,496 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Fleet report formatting
+
+use super::{FleetReport, ImageRecord, TrendPoint};
+
+/// Chart size in pixels
+const CHART_WIDTH: f64 = 560.0;
+const CHART_HEIGHT: f64 = 200.0;
+const CHART_PADDING: f64 = 30.0;
+
+fn percent(value: Option<f64>) -> String {
+    value
+        .map(|v| format!("{:.1}%", v))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+fn number(value: Option<f64>) -> String {
+    value
+        .map(|v| format!("{:.1}", v))
+        .unwrap_or_else(|| "-".to_string())
+}
+
+/// Format fleet report as text
+pub fn format_report(report: &FleetReport, verbose: bool) -> String {
+    let mut output = String::new();
+    let run = &report.current;
+    let summary = &run.summary;
+
+    output.push_str("🚢 Fleet Report\n");
+    output.push_str("===============\n\n");
+    output.push_str(&format!("Results: {}\n", run.path));
+    if let Some(timestamp) = &run.timestamp {
+        output.push_str(&format!("Run: {}\n", timestamp));
+    }
+    output.push_str(&format!("Generated: {}\n\n", report.generated_at));
+
+    output.push_str("📊 Summary\n");
+    output.push_str("----------\n");
+    output.push_str(&format!(
+        "Images: {} ({} result files)\n",
+        summary.images, run.files_read
+    ));
+    output.push_str(&format!(
+        "Validated: {} ({} failing), average compliance {}\n",
+        summary.validated,
+        summary.failing_validation,
+        percent(summary.average_compliance)
+    ));
+    output.push_str(&format!(
+        "Scored: {}, average risk score {}\n",
+        summary.scored,
+        number(summary.average_score)
+    ));
+    output.push_str(&format!(
+        "CVEs: {} critical, {} high, {} medium, {} low across {} scanned images\n\n",
+        summary.cves.critical,
+        summary.cves.high,
+        summary.cves.medium,
+        summary.cves.low,
+        summary.scanned
+    ));
+
+    if !report.trend.is_empty() {
+        output.push_str("📈 Trend\n");
+        output.push_str("--------\n");
+        for point in &report.trend {
+            output.push_str(&format!(
+                "{}: {} images, compliance {}, score {}, {} CVEs ({} critical)\n",
+                point.label,
+                point.images,
+                percent(point.average_compliance),
+                number(point.average_score),
+                point.cves.total(),
+                point.cves.critical
+            ));
+        }
+        output.push('\n');
+    }
+
+    let mut images: Vec<&ImageRecord> = run.images.iter().collect();
+    images.sort_by_key(|image| std::cmp::Reverse(attention(image)));
+
+    output.push_str("🖼️  Images\n");
+    output.push_str("----------\n");
+    for image in images {
+        output.push_str(&format!("{}\n", image.display_name()));
+        if image.name.is_some() {
+            output.push_str(&format!("   Path: {}\n", image.image));
+        }
+        if let Some(os) = &image.os {
+            output.push_str(&format!("   OS: {}\n", os));
+        }
+        if let Some(validation) = &image.validation {
+            output.push_str(&format!(
+                "   Validate ({}): {:.1}%, {} failed\n",
+                validation.policy, validation.compliance_score, validation.failed
+            ));
+        }
+        if let Some(score) = &image.score {
+            output.push_str(&format!(
+                "   Score: {} / 100 ({})\n",
+                score.overall, score.grade
+            ));
+        }
+        if let Some(cves) = &image.cves {
+            output.push_str(&format!(
+                "   CVEs: {} critical, {} high, {} medium, {} low\n",
+                cves.critical, cves.high, cves.medium, cves.low
+            ));
+        }
+        if verbose {
+            output.push_str(&format!("   └─ {}\n", image.sources.join(", ")));
+        }
+    }
+    output.push('\n');
+
+    if !run.warnings.is_empty() {
+        output.push_str("⚠️  Skipped Files\n");
+        output.push_str("----------------\n");
+        for warning in &run.warnings {
+            output.push_str(&format!("{}\n", warning));
+        }
+        output.push('\n');
+    }
+
+    output
+}
+
+/// Rough ordering so that images needing attention are listed first
+fn attention(image: &ImageRecord) -> usize {
+    let cves = image
+        .cves
+        .map(|c| c.critical * 1000 + c.high * 100 + c.medium)
+        .unwrap_or(0);
+    let failed = image.validation.as_ref().map(|v| v.failed).unwrap_or(0);
+    cves + failed * 10
+}
+
+/// Format per-image results as CSV
+pub fn format_csv(report: &FleetReport) -> String {
+    let mut output = String::from(
+        "Image,OS,Policy,Compliance,Failed Rules,Risk Score,Grade,Critical CVEs,High CVEs,Medium CVEs,Low CVEs,Catalog ID\n",
+    );
+
+    for image in &report.current.images {
+        let validation = image.validation.as_ref();
+        let score = image.score.as_ref();
+        let cve = |count: fn(&super::CveCounts) -> usize| {
+            image
+                .cves
+                .as_ref()
+                .map(|c| count(c).to_string())
+                .unwrap_or_default()
+        };
+        output.push_str(&format!(
+            "\"{}\",\"{}\",\"{}\",{},{},{},{},{},{},{},{},{}\n",
+            image.image.replace('"', "\"\""),
+            image.os.as_deref().unwrap_or(""),
+            validation.map(|v| v.policy.as_str()).unwrap_or(""),
+            validation
+                .map(|v| format!("{:.1}", v.compliance_score))
+                .unwrap_or_default(),
+            validation.map(|v| v.failed.to_string()).unwrap_or_default(),
+            score.map(|s| s.overall.to_string()).unwrap_or_default(),
+            score.map(|s| s.grade.as_str()).unwrap_or(""),
+            cve(|c| c.critical),
+            cve(|c| c.high),
+            cve(|c| c.medium),
+            cve(|c| c.low),
+            image.catalog_id.as_deref().unwrap_or("")
+        ));
+    }
+
+    output
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Table cell with a sort key, empty values sorting last
+fn sort_cell(text: String, key: Option<f64>) -> String {
+    let key = key.map(|k| k.to_string()).unwrap_or_default();
+    format!("<td data-sort=\"{}\">{}</td>", key, text)
+}
+
+/// SVG line chart of one series per (name, color, values)
+fn line_chart(title: &str, labels: &[&str], series: &[(&str, &str, Vec<Option<f64>>)]) -> String {
+    let max = series
+        .iter()
+        .flat_map(|(_, _, values)| values.iter().flatten())
+        .fold(0.0_f64, |a, &b| a.max(b));
+    let max = if max > 0.0 { max } else { 1.0 };
+
+    let step = if labels.len() > 1 {
+        (CHART_WIDTH - 2.0 * CHART_PADDING) / (labels.len() - 1) as f64
+    } else {
+        0.0
+    };
+    let x = |i: usize| CHART_PADDING + step * i as f64;
+    let y = |v: f64| CHART_HEIGHT - CHART_PADDING - v / max * (CHART_HEIGHT - 2.0 * CHART_PADDING);
+
+    let mut svg = String::new();
+    svg.push_str("<div class=\"chart\">\n");
+    svg.push_str(&format!("<h3>{}</h3>\n", escape(title)));
+    svg.push_str(&format!(
+        "<svg width=\"{}\" height=\"{}\" viewBox=\"0 0 {} {}\">\n",
+        CHART_WIDTH, CHART_HEIGHT, CHART_WIDTH, CHART_HEIGHT
+    ));
+    svg.push_str(&format!(
+        "<line x1=\"{p}\" y1=\"{b}\" x2=\"{r}\" y2=\"{b}\" stroke=\"#999\"/>\n",
+        p = CHART_PADDING,
+        b = CHART_HEIGHT - CHART_PADDING,
+        r = CHART_WIDTH - CHART_PADDING
+    ));
+    svg.push_str(&format!(
+        "<text x=\"2\" y=\"{}\" font-size=\"10\">{:.0}</text>\n",
+        CHART_PADDING, max
+    ));
+    for (i, label) in labels.iter().enumerate() {
+        svg.push_str(&format!(
+            "<text x=\"{:.1}\" y=\"{}\" font-size=\"10\" text-anchor=\"middle\">{}</text>\n",
+            x(i),
+            CHART_HEIGHT - 10.0,
+            escape(label)
+        ));
+    }
+
+    for (name, color, values) in series {
+        let points: Vec<(f64, f64)> = values
+            .iter()
+            .enumerate()
+            .filter_map(|(i, v)| v.map(|v| (x(i), y(v))))
+            .collect();
+        if points.is_empty() {
+            continue;
+        }
+        let path: Vec<String> = points
+            .iter()
+            .map(|(px, py)| format!("{:.1},{:.1}", px, py))
+            .collect();
+        svg.push_str(&format!(
+            "<polyline fill=\"none\" stroke=\"{}\" stroke-width=\"2\" points=\"{}\"><title>{}</title></polyline>\n",
+            color,
+            path.join(" "),
+            escape(name)
+        ));
+        for (px, py) in &points {
+            svg.push_str(&format!(
+                "<circle cx=\"{:.1}\" cy=\"{:.1}\" r=\"3\" fill=\"{}\"/>\n",
+                px, py, color
+            ));
+        }
+    }
+    svg.push_str("</svg>\n");
+
+    svg.push_str("<p class=\"legend\">");
+    for (name, color, _) in series {
+        svg.push_str(&format!(
+            "<span style=\"color: {}\">■</span> {} ",
+            color,
+            escape(name)
+        ));
+    }
+    svg.push_str("</p>\n</div>\n");
+
+    svg
+}
+
+fn trend_charts(trend: &[TrendPoint]) -> String {
+    let labels: Vec<&str> = trend.iter().map(|p| p.label.as_str()).collect();
+    let mut html = String::new();
+
+    html.push_str(&line_chart(
+        "Average Compliance and Risk Score",
+        &labels,
+        &[
+            (
+                "Compliance %",
+                "#388e3c",
+                trend.iter().map(|p| p.average_compliance).collect(),
+            ),
+            (
+                "Risk score",
+                "#2196f3",
+                trend.iter().map(|p| p.average_score).collect(),
+            ),
+        ],
+    ));
+    html.push_str(&line_chart(
+        "CVEs",
+        &labels,
+        &[
+            (
+                "Critical",
+                "#d32f2f",
+                trend.iter().map(|p| Some(p.cves.critical as f64)).collect(),
+            ),
+            (
+                "High",
+                "#f57c00",
+                trend.iter().map(|p| Some(p.cves.high as f64)).collect(),
+            ),
+            (
+                "All",
+                "#616161",
+                trend.iter().map(|p| Some(p.cves.total() as f64)).collect(),
+            ),
+        ],
+    ));
+
+    html
+}
+
+/// Sorts a table by the clicked column, using data-sort keys when present
+const SORT_SCRIPT: &str = r#"<script>
+document.querySelectorAll("table.sortable th").forEach(function (th, column) {
+  th.addEventListener("click", function () {
+    var table = th.closest("table");
+    var body = table.tBodies[0];
+    var ascending = th.dataset.order !== "asc";
+    table.querySelectorAll("th").forEach(function (h) { delete h.dataset.order; });
+    th.dataset.order = ascending ? "asc" : "desc";
+    var rows = Array.from(body.rows);
+    rows.sort(function (a, b) {
+      var x = a.cells[column], y = b.cells[column];
+      var kx = x.dataset.sort, ky = y.dataset.sort;
+      var result;
+      if (kx !== undefined && ky !== undefined) {
+        if (kx === "" || ky === "") return kx === ky ? 0 : (kx === "" ? 1 : -1);
+        result = parseFloat(kx) - parseFloat(ky);
+      } else {
+        result = x.textContent.localeCompare(y.textContent);
+      }
+      return ascending ? result : -result;
+    });
+    rows.forEach(function (row) { body.appendChild(row); });
+  });
+});
+</script>
+"#;
+
+/// Format fleet report as a standalone HTML dashboard
+pub fn format_html(report: &FleetReport) -> String {
+    let run = &report.current;
+    let summary = &run.summary;
+    let mut html = String::new();
+
+    html.push_str("<!DOCTYPE html>\n");
+    html.push_str("<html>\n<head>\n");
+    html.push_str("<meta charset=\"utf-8\">\n");
+    html.push_str("<title>Fleet Report</title>\n");
+    html.push_str("<style>\n");
+    html.push_str("body { font-family: Arial, sans-serif; max-width: 1400px; margin: 0 auto; padding: 20px; }\n");
+    html.push_str("h1, h2, h3 { color: #333; }\n");
+    html.push_str(".cards { display: flex; flex-wrap: wrap; gap: 10px; }\n");
+    html.push_str(
+        ".card { background: #f0f0f0; padding: 15px; border-radius: 5px; min-width: 160px; }\n",
+    );
+    html.push_str(".card .value { font-size: 1.8em; font-weight: bold; }\n");
+    html.push_str(".charts { display: flex; flex-wrap: wrap; gap: 20px; }\n");
+    html.push_str(".legend { font-size: 0.9em; }\n");
+    html.push_str(".critical { color: #d32f2f; font-weight: bold; }\n");
+    html.push_str(".fail { color: #d32f2f; }\n");
+    html.push_str("table { border-collapse: collapse; width: 100%; margin: 10px 0; }\n");
+    html.push_str("th, td { padding: 8px; text-align: left; border-bottom: 1px solid #ddd; }\n");
+    html.push_str("th { background-color: #2196f3; color: white; cursor: pointer; }\n");
+    html.push_str("th[data-order=asc]::after { content: \" ▲\"; }\n");
+    html.push_str("th[data-order=desc]::after { content: \" ▼\"; }\n");
+    html.push_str("code { font-size: 0.9em; }\n");
+    html.push_str("</style>\n");
+    html.push_str("</head>\n<body>\n");
+
+    html.push_str("<h1>🚢 Fleet Report</h1>\n");
+    html.push_str(&format!(
+        "<p><strong>Results:</strong> {} &middot; <strong>Run:</strong> {} &middot; <strong>Generated:</strong> {}</p>\n",
+        escape(&run.path),
+        escape(run.timestamp.as_deref().unwrap_or("-")),
+        report.generated_at
+    ));
+
+    let cards = [
+        ("Images", summary.images.to_string()),
+        ("Average compliance", percent(summary.average_compliance)),
+        ("Failing validation", summary.failing_validation.to_string()),
+        ("Average risk score", number(summary.average_score)),
+        ("Critical CVEs", summary.cves.critical.to_string()),
+        ("High CVEs", summary.cves.high.to_string()),
+    ];
+    html.push_str("<div class=\"cards\">\n");
+    for (label, value) in cards {
+        html.push_str(&format!(
+            "<div class=\"card\"><div>{}</div><div class=\"value\">{}</div></div>\n",
+            label, value
+        ));
+    }
+    html.push_str("</div>\n");
+
+    if !report.trend.is_empty() {
+        html.push_str("<h2>Trends</h2>\n");
+        html.push_str("<div class=\"charts\">\n");
+        html.push_str(&trend_charts(&report.trend));
+        html.push_str("</div>\n");
+
+        html.push_str("<table class=\"sortable\">\n");
+        html.push_str("<thead><tr><th>Run</th><th>Date</th><th>Images</th><th>Compliance</th><th>Risk Score</th><th>Critical</th><th>High</th><th>All CVEs</th></tr></thead>\n<tbody>\n");
+        for point in &report.trend {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td>{}{}{}{}{}{}</tr>\n",
+                escape(&point.label),
+                escape(point.timestamp.as_deref().unwrap_or("")),
+                sort_cell(point.images.to_string(), Some(point.images as f64)),
+                sort_cell(percent(point.average_compliance), point.average_compliance),
+                sort_cell(number(point.average_score), point.average_score),
+                sort_cell(
+                    point.cves.critical.to_string(),
+                    Some(point.cves.critical as f64)
+                ),
+                sort_cell(point.cves.high.to_string(), Some(point.cves.high as f64)),
+                sort_cell(
+                    point.cves.total().to_string(),
+                    Some(point.cves.total() as f64)
+                )
+            ));
+        }
+        html.push_str("</tbody>\n</table>\n");
+    }
+
+    html.push_str("<h2>Images</h2>\n");
+    html.push_str("<table class=\"sortable\">\n");
+    html.push_str("<thead><tr><th>Image</th><th>OS</th><th>Policy</th><th>Compliance</th><th>Failed</th><th>Risk Score</th><th>Grade</th><th>Critical</th><th>High</th><th>Medium</th><th>Low</th></tr></thead>\n<tbody>\n");
+    for image in &run.images {
+        let validation = image.validation.as_ref();
+        let score = image.score.as_ref();
+        let cve = |count: Option<usize>| {
+            sort_cell(
+                count.map(|c| c.to_string()).unwrap_or_default(),
+                count.map(|c| c as f64),
+            )
+        };
+
+        let critical = image.cves.map(|c| c.critical);
+        let critical_cell = match critical {
+            Some(count) if count > 0 => format!(
+                "<td data-sort=\"{}\" class=\"critical\">{}</td>",
+                count, count
+            ),
+            _ => cve(critical),
+        };
+        let failed = validation.map(|v| v.failed);
+        let failed_cell = match failed {
+            Some(count) if count > 0 => {
+                format!("<td data-sort=\"{}\" class=\"fail\">{}</td>", count, count)
+            }
+            _ => cve(failed),
+        };
+
+        html.push_str(&format!(
+            "<tr><td><code>{}</code></td><td>{}</td><td>{}</td>{}{}{}<td>{}</td>{}{}{}{}</tr>\n",
+            escape(image.display_name()),
+            escape(image.os.as_deref().unwrap_or("")),
+            escape(validation.map(|v| v.policy.as_str()).unwrap_or("")),
+            sort_cell(
+                percent(validation.map(|v| v.compliance_score)),
+                validation.map(|v| v.compliance_score)
+            ),
+            failed_cell,
+            sort_cell(
+                score.map(|s| s.overall.to_string()).unwrap_or_default(),
+                score.map(|s| f64::from(s.overall))
+            ),
+            escape(score.map(|s| s.grade.as_str()).unwrap_or("")),
+            critical_cell,
+            cve(image.cves.map(|c| c.high)),
+            cve(image.cves.map(|c| c.medium)),
+            cve(image.cves.map(|c| c.low))
+        ));
+    }
+    html.push_str("</tbody>\n</table>\n");
+
+    if !run.warnings.is_empty() {
+        html.push_str("<h2>Skipped Files</h2>\n<ul>\n");
+        for warning in &run.warnings {
+            html.push_str(&format!("<li>{}</li>\n", escape(warning)));
+        }
+        html.push_str("</ul>\n");
+    }
+
+    html.push_str(SORT_SCRIPT);
+    html.push_str("</body>\n</html>\n");
+
+    html
+}
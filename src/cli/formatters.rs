@@ -3,6 +3,8 @@
 
 use anyhow::Result;
 use guestkit::guestfs::inspect_enhanced::*;
+use guestkit::guestfs::LuksInfo;
+use guestkit::guestfs::WindowsScheduledTask;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
@@ -11,6 +13,9 @@ use std::collections::HashMap;
 pub struct InspectionReport {
     #[serde(skip_serializing_if = "Option::is_none")]
     pub image_path: Option<String>,
+    /// Every OS found on a multi-boot image; `os` describes the first
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub os_instances: Option<Vec<OsInstance>>,
     pub os: OsInfo,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub system_config: Option<SystemConfig>,
@@ -42,11 +47,15 @@ pub struct InspectionReport {
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WindowsInfo {
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub system: Option<WindowsSystemInfo>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub software: Option<Vec<WindowsApplication>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub services: Option<Vec<WindowsService>>,
     #[serde(skip_serializing_if = "Option::is_none")]
+    pub scheduled_tasks: Option<Vec<WindowsScheduledTask>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
     pub network_adapters: Option<Vec<WindowsNetworkAdapter>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub updates: Option<Vec<WindowsUpdate>>,
@@ -54,6 +63,17 @@ pub struct WindowsInfo {
     pub event_logs: Option<Vec<WindowsEventLogEntry>>,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OsInstance {
+    pub root: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub os_type: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distribution: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub product_name: Option<String>,
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct OsInfo {
     pub root: String,
@@ -139,6 +159,18 @@ pub struct StorageInfo {
     pub swap_devices: Option<Vec<String>>,
     #[serde(skip_serializing_if = "Option::is_none")]
     pub fstab_mounts: Option<Vec<FstabMount>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub encrypted_volumes: Option<Vec<EncryptedVolume>>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct EncryptedVolume {
+    pub device: String,
+    #[serde(flatten)]
+    pub luks: LuksInfo,
+    /// Mapped device, if the volume was unlocked with --key
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unlocked_as: Option<String>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
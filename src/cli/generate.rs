@@ -0,0 +1,129 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Generate command - libvirt domain XML and QEMU command lines for an image
+
+use crate::cli::catalog::parse_image_ref;
+use crate::cli::output::parse_size;
+use crate::cli::plain;
+use anyhow::{Context, Result};
+use clap::{Args, Subcommand};
+use colored::*;
+use guestkit::converters::DiskConverter;
+use guestkit::core::escape::shell_quote;
+use guestkit::export::DomainSpec;
+use guestkit::guestfs::ImageProperties;
+use std::path::PathBuf;
+
+#[derive(Debug, Args)]
+pub struct GenerateCommand {
+    #[command(subcommand)]
+    pub target: GenerateTarget,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum GenerateTarget {
+    /// libvirt domain XML with firmware, disk bus and NIC model for the guest OS
+    Libvirt(DomainArgs),
+
+    /// qemu-system command line with firmware, disk bus and NIC model for the guest OS
+    Qemu(DomainArgs),
+}
+
+#[derive(Debug, Args)]
+pub struct DomainArgs {
+    /// Disk image
+    #[arg(value_parser = parse_image_ref)]
+    pub image: PathBuf,
+
+    /// Domain name (default: image file name)
+    #[arg(long)]
+    pub name: Option<String>,
+
+    /// Memory, e.g. 4G (default: 2G, 4G for Windows)
+    #[arg(short, long, value_parser = parse_size)]
+    pub memory: Option<u64>,
+
+    /// Virtual CPUs (default: 2)
+    #[arg(long)]
+    pub vcpus: Option<u32>,
+
+    /// libvirt network for the NIC
+    #[arg(long, default_value = "default")]
+    pub network: String,
+
+    /// Output file (default: stdout)
+    #[arg(short, long, value_name = "FILE")]
+    pub output: Option<PathBuf>,
+}
+
+impl GenerateCommand {
+    pub fn execute(&self) -> Result<()> {
+        let (args, text) = match &self.target {
+            GenerateTarget::Libvirt(args) => (args, args.spec()?.to_libvirt_xml()),
+            GenerateTarget::Qemu(args) => {
+                let command = args
+                    .spec()?
+                    .to_qemu_args()
+                    .iter()
+                    .map(|arg| shell_quote(arg))
+                    .collect::<Vec<_>>()
+                    .join(" \\\n    ");
+                (args, command + "\n")
+            }
+        };
+
+        match &args.output {
+            Some(path) => {
+                std::fs::write(path, text)
+                    .with_context(|| format!("Failed to write {}", path.display()))?;
+                eprintln!("{} Wrote {}", plain::text("✓").green(), path.display());
+            }
+            None => print!("{}", text),
+        }
+        Ok(())
+    }
+}
+
+impl DomainArgs {
+    fn spec(&self) -> Result<DomainSpec> {
+        // The domain outlives this directory
+        let image = self
+            .image
+            .canonicalize()
+            .with_context(|| format!("Failed to open {}", self.image.display()))?;
+        let format = DiskConverter::new()
+            .detect_format(&image)
+            .with_context(|| format!("Failed to detect the format of {}", image.display()))?;
+        let properties = ImageProperties::detect(&image)
+            .with_context(|| format!("Failed to inspect {}", image.display()))?;
+
+        let name = self.name.clone().unwrap_or_else(|| {
+            image
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_else(|| "guest".to_string())
+        });
+        let mut spec =
+            DomainSpec::for_image(&name, &image, format.as_str(), &properties, &self.network);
+        if let Some(memory) = self.memory {
+            spec.memory_mib = memory >> 20;
+        }
+        if let Some(vcpus) = self.vcpus {
+            spec.vcpus = vcpus;
+        }
+
+        // On stderr, so the output can be piped into virsh or a shell
+        eprintln!(
+            "{} {}: {} firmware, {} disk, {} NIC",
+            "Guest".bold(),
+            properties
+                .product_name
+                .as_deref()
+                .or(properties.os_type.as_deref())
+                .unwrap_or("unknown OS"),
+            if spec.uefi { "UEFI" } else { "BIOS" },
+            spec.disks[0].bus,
+            spec.nics[0].model
+        );
+        Ok(spec)
+    }
+}
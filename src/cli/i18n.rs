@@ -0,0 +1,195 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Message catalog for user-facing CLI output
+//!
+//! Messages live in Fluent files under `locales/<lang>/guestctl.ftl` and are
+//! compiled into the binary. The locale comes from `--lang`, then
+//! `GUESTCTL_LANG`, then the usual `LC_ALL` / `LC_MESSAGES` / `LANG`
+//! variables. Ids missing from a catalog fall back to English.
+
+use fluent_bundle::concurrent::FluentBundle;
+use fluent_bundle::{FluentArgs, FluentResource, FluentValue};
+use once_cell::sync::OnceCell;
+use unic_langid::LanguageIdentifier;
+
+/// Default locale and fallback for missing messages
+pub const DEFAULT_LOCALE: &str = "en";
+
+/// Bundled catalogs
+const CATALOGS: &[(&str, &str)] = &[
+    ("en", include_str!("../../locales/en/guestctl.ftl")),
+    ("es", include_str!("../../locales/es/guestctl.ftl")),
+    ("de", include_str!("../../locales/de/guestctl.ftl")),
+];
+
+static CATALOG: OnceCell<Catalog> = OnceCell::new();
+
+/// Loaded message bundles for the active locale
+pub struct Catalog {
+    bundle: FluentBundle<FluentResource>,
+    fallback: Option<FluentBundle<FluentResource>>,
+}
+
+fn load_bundle(locale: &'static str) -> FluentBundle<FluentResource> {
+    let source = CATALOGS
+        .iter()
+        .find(|(lang, _)| *lang == locale)
+        .map(|(_, source)| *source)
+        .unwrap_or(CATALOGS[0].1);
+
+    let langid: LanguageIdentifier = locale.parse().unwrap_or_default();
+    let mut bundle = FluentBundle::new_concurrent(vec![langid]);
+    // Isolation marks show up as stray characters in terminals
+    bundle.set_use_isolating(false);
+
+    let resource =
+        FluentResource::try_new(source.to_string()).unwrap_or_else(|(resource, _)| resource);
+    let _ = bundle.add_resource(resource);
+    bundle
+}
+
+impl Catalog {
+    /// Load the catalog for a supported locale
+    pub fn new(locale: &'static str) -> Self {
+        Self {
+            bundle: load_bundle(locale),
+            fallback: (locale != DEFAULT_LOCALE).then(|| load_bundle(DEFAULT_LOCALE)),
+        }
+    }
+
+    /// Format a message, returning the id itself if no catalog has it
+    pub fn format(&self, id: &str, args: Option<&FluentArgs>) -> String {
+        for bundle in std::iter::once(&self.bundle).chain(self.fallback.as_ref()) {
+            if let Some(pattern) = bundle.get_message(id).and_then(|m| m.value()) {
+                let mut errors = Vec::new();
+                return bundle
+                    .format_pattern(pattern, args, &mut errors)
+                    .into_owned();
+            }
+        }
+        id.to_string()
+    }
+}
+
+/// Map a locale name such as `de_DE.UTF-8` or `es-MX` to a bundled catalog
+pub fn negotiate(requested: &str) -> &'static str {
+    let language = requested
+        .split(['.', '@'])
+        .next()
+        .unwrap_or_default()
+        .split(['_', '-'])
+        .next()
+        .unwrap_or_default()
+        .to_lowercase();
+
+    CATALOGS
+        .iter()
+        .map(|(lang, _)| *lang)
+        .find(|lang| *lang == language)
+        .unwrap_or(DEFAULT_LOCALE)
+}
+
+/// Locale requested by the environment
+pub fn detect_locale() -> &'static str {
+    ["GUESTCTL_LANG", "LC_ALL", "LC_MESSAGES", "LANG"]
+        .iter()
+        .filter_map(|var| std::env::var(var).ok())
+        .find(|value| !value.is_empty())
+        .map(|value| negotiate(&value))
+        .unwrap_or(DEFAULT_LOCALE)
+}
+
+/// Select the locale for this process
+///
+/// Must be called before the first message is formatted; output defaults
+/// to English otherwise.
+pub fn init(lang: Option<&str>) {
+    let locale = match lang {
+        Some(lang) => negotiate(lang),
+        None => detect_locale(),
+    };
+    let _ = CATALOG.set(Catalog::new(locale));
+}
+
+fn catalog() -> &'static Catalog {
+    CATALOG.get_or_init(|| Catalog::new(DEFAULT_LOCALE))
+}
+
+/// Translate a message without arguments
+pub fn tr(id: &str) -> String {
+    catalog().format(id, None)
+}
+
+/// Translate a message with named arguments
+pub fn tr_args(id: &str, args: &[(&str, FluentValue)]) -> String {
+    let mut fluent_args = FluentArgs::new();
+    for (name, value) in args {
+        fluent_args.set(*name, value.clone());
+    }
+    catalog().format(id, Some(&fluent_args))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn message_ids(source: &str) -> Vec<&str> {
+        source
+            .lines()
+            .filter(|line| !line.starts_with([' ', '#', '}']) && line.contains(" = "))
+            .filter_map(|line| line.split(" = ").next())
+            .collect()
+    }
+
+    #[test]
+    fn test_negotiate() {
+        assert_eq!(negotiate("de_DE.UTF-8"), "de");
+        assert_eq!(negotiate("es-MX"), "es");
+        assert_eq!(negotiate("C"), "en");
+        assert_eq!(negotiate("fr_FR.UTF-8"), "en");
+    }
+
+    #[test]
+    fn test_catalogs_define_every_message() {
+        let reference = message_ids(CATALOGS[0].1);
+        assert!(!reference.is_empty());
+        for (lang, source) in &CATALOGS[1..] {
+            let ids = message_ids(source);
+            for id in &reference {
+                assert!(ids.contains(id), "{} catalog is missing {}", lang, id);
+            }
+        }
+    }
+
+    #[test]
+    fn test_format_with_plurals() {
+        let mut args = FluentArgs::new();
+        args.set("passed", 3);
+        args.set("warnings", 1);
+        args.set("failures", 2);
+
+        let en = Catalog::new("en");
+        assert_eq!(
+            en.format("doctor-summary", Some(&args)),
+            "3 passed, 1 warning, 2 failures"
+        );
+
+        let de = Catalog::new("de");
+        assert_eq!(
+            de.format("doctor-summary", Some(&args)),
+            "3 bestanden, 1 Warnung, 2 Fehler"
+        );
+    }
+
+    #[test]
+    fn test_fallback() {
+        let es = Catalog::new("es");
+        assert_eq!(es.format("no-such-message", None), "no-such-message");
+
+        let mut args = FluentArgs::new();
+        args.set("path", "/tmp/informe-ñ.json");
+        assert_eq!(
+            es.format("written-crash-report", Some(&args)),
+            "✅ Informe de fallos escrito en: /tmp/informe-ñ.json"
+        );
+    }
+}
@@ -0,0 +1,137 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Import-ova command - unpack an OVA appliance and convert its disks
+
+use crate::cli::output::format_size;
+use crate::cli::plain;
+use anyhow::{Context, Result};
+use clap::Args;
+use colored::*;
+use guestkit::converters::{DiskConverter, OvaArchive};
+use guestkit::core::ProgressReporter;
+use std::path::PathBuf;
+
+#[derive(Debug, Args)]
+pub struct ImportOvaCommand {
+    /// OVA appliance
+    pub ova: PathBuf,
+
+    /// Directory for the converted disks
+    #[arg(short, long, default_value = ".")]
+    pub output_dir: PathBuf,
+
+    /// Disk format to convert to (qcow2, raw, vmdk, ...)
+    #[arg(short, long, default_value = "qcow2")]
+    pub format: String,
+
+    /// Write a libvirt domain XML for the appliance
+    #[arg(long, value_name = "FILE")]
+    pub libvirt_xml: Option<PathBuf>,
+
+    /// Keep the VMDKs extracted from the archive next to the converted disks
+    #[arg(long)]
+    pub keep_vmdk: bool,
+
+    /// Print the summary as JSON
+    #[arg(long)]
+    pub json: bool,
+}
+
+impl ImportOvaCommand {
+    pub fn execute(&self) -> Result<()> {
+        let ova = OvaArchive::open(&self.ova)
+            .with_context(|| format!("Failed to open {}", self.ova.display()))?;
+        if !ova.has_manifest() && !self.json {
+            println!(
+                "{} {} has no manifest, disks are not checksummed",
+                plain::text("⚠").yellow(),
+                self.ova.display()
+            );
+        }
+        let disk_count = ova.appliance()?.disks.len();
+
+        let progress = ProgressReporter::spinner(&format!("Importing {}", self.ova.display()));
+        let import = ova
+            .import(
+                &DiskConverter::new(),
+                &self.output_dir,
+                &self.format,
+                self.keep_vmdk,
+                |index, percent| {
+                    progress.set_message(format!(
+                        "Converting disk {}/{}: {:.0}%",
+                        index + 1,
+                        disk_count,
+                        percent
+                    ));
+                    true
+                },
+            )
+            .inspect_err(|_| progress.abandon_with_message("Import failed"))
+            .with_context(|| format!("Failed to import {}", self.ova.display()))?;
+        progress.finish_and_clear();
+
+        if let Some(xml_path) = &self.libvirt_xml {
+            std::fs::write(xml_path, import.domain_spec().to_libvirt_xml())
+                .with_context(|| format!("Failed to write {}", xml_path.display()))?;
+        }
+
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&import)?);
+            return Ok(());
+        }
+
+        let appliance = &import.appliance;
+        println!("{} Imported {}", plain::text("✓").green(), appliance.name.bold());
+        if let Some(os) = &appliance.os {
+            println!("  OS:       {}", os);
+        }
+        println!("  CPUs:     {}", appliance.cpus);
+        println!("  Memory:   {} MiB", appliance.memory_mib);
+        println!("  Firmware: {}", appliance.firmware);
+
+        println!("\n{}", "Disks".bold());
+        for disk in &import.disks {
+            println!(
+                "  {:<5} {:>10}  {} ({}, {} on disk)",
+                disk.disk.bus,
+                format_size(disk.disk.capacity),
+                disk.path.display().to_string().bright_blue(),
+                disk.format,
+                format_size(disk.size)
+            );
+        }
+
+        if !appliance.nics.is_empty() {
+            println!("\n{}", "Network".bold());
+            for nic in &appliance.nics {
+                println!(
+                    "  {:<20} {:<8} {}{}",
+                    nic.name,
+                    nic.model.as_deref().unwrap_or("-"),
+                    nic.network.as_deref().unwrap_or("-"),
+                    nic.mac
+                        .as_deref()
+                        .map(|mac| format!(" ({})", mac))
+                        .unwrap_or_default()
+                );
+            }
+        }
+
+        if !import.verified.is_empty() {
+            println!(
+                "\n{} {} files match the manifest",
+                plain::text("✓").green(),
+                import.verified.len()
+            );
+        }
+        if let Some(xml_path) = &self.libvirt_xml {
+            println!(
+                "{} libvirt domain written to {} (virsh define {})",
+                plain::text("✓").green(),
+                xml_path.display(),
+                xml_path.display()
+            );
+        }
+        Ok(())
+    }
+}
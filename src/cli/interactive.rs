@@ -2,7 +2,10 @@
 //! Interactive REPL mode for guestctl CLI
 
 use super::errors::errors;
+use super::target;
 use anyhow::{Context, Result};
+use guestkit::guestfs::mount_plan::MountSource;
+use guestkit::guestfs::preview::DEFAULT_PREVIEW_BYTES;
 use guestkit::Guestfs;
 use owo_colors::OwoColorize;
 use rustyline::completion::{Completer, Pair};
@@ -340,6 +343,8 @@ impl InteractiveSession {
         handle
             .add_drive_ro(disk_path.to_str().unwrap())
             .context("Failed to add drive")?;
+        target::add_disks(&mut handle, true)?;
+        target::select(&mut handle);
 
         // Launch
         println!("  {} Launching appliance...", "→".truecolor(222, 115, 86));
@@ -347,7 +352,7 @@ impl InteractiveSession {
 
         // Auto-inspect
         println!("  {} Inspecting disk...", "→".truecolor(222, 115, 86));
-        let roots = handle.inspect_os().unwrap_or_default();
+        let roots = target::os_roots(&mut handle)?;
         let current_root = roots.first().cloned();
 
         if let Some(ref root) = current_root {
@@ -374,19 +379,32 @@ impl InteractiveSession {
         // Auto-mount filesystems
         if let Some(ref root) = current_root {
             println!("  {} Auto-mounting filesystems...", "→".truecolor(222, 115, 86));
-            match handle.inspect_get_mountpoints(root) {
-                Ok(mountpoints) => {
-                    // Sort by mountpoint length (mount / before /boot, etc.)
-                    let mut sorted_mounts: Vec<_> = mountpoints.into_iter().collect();
-                    sorted_mounts.sort_by_key(|(mp, _)| mp.len());
-
-                    for (mountpoint, device) in sorted_mounts {
-                        if let Err(e) = handle.mount_ro(&device, &mountpoint) {
-                            println!("  {} Failed to mount {} at {}: {}", "⚠".yellow(), device, mountpoint, e);
+            match handle.inspect_mount_plan(root) {
+                Ok(plan) => {
+                    // The plan mounts / first and follows the guest's fstab
+                    for mount in &plan.mounts {
+                        let (source, result) = match &mount.source {
+                            MountSource::Device(device) => (
+                                device,
+                                handle.mount_ro_options(
+                                    &mount.options.join(","),
+                                    device,
+                                    &mount.mountpoint,
+                                ),
+                            ),
+                            MountSource::Bind(source) => {
+                                (source, handle.mount_bind_ro(source, &mount.mountpoint))
+                            }
+                        };
+                        if let Err(e) = result {
+                            println!("  {} Failed to mount {} at {}: {}", "⚠".yellow(), source, mount.mountpoint, e);
                         } else {
-                            println!("  {} Mounted {} at {} (ro)", "✓".green(), device.bright_white(), mountpoint.truecolor(222, 115, 86));
+                            println!("  {} Mounted {} at {} (ro)", "✓".green(), source.bright_white(), mount.mountpoint.truecolor(222, 115, 86));
                         }
                     }
+                    for skipped in &plan.skipped {
+                        println!("  {} Skipped {} at {}: {}", "→".truecolor(222, 115, 86), skipped.spec, skipped.mountpoint, skipped.reason);
+                    }
                 }
                 Err(e) => {
                     println!("  {} Warning: Could not get mountpoints: {}", "⚠".yellow(), e);
@@ -1062,10 +1080,11 @@ impl InteractiveSession {
         }
 
         let path = args[0];
-        let content = self
+        let window = self
             .handle
-            .cat(path)
+            .preview_file(path, DEFAULT_PREVIEW_BYTES)
             .with_context(|| format!("Failed to read file: {}", path))?;
+        let content = super::preview::preview_text(&window);
 
         println!();
         print!("{}", content);
@@ -1091,16 +1110,14 @@ impl InteractiveSession {
             10
         };
 
-        let content = self
+        let reader = self
             .handle
-            .cat(path)
+            .open_file(path)
             .with_context(|| format!("Failed to read file: {}", path))?;
 
         println!();
-        for (i, line) in content.lines().enumerate() {
-            if i >= lines {
-                break;
-            }
+        for line in std::io::BufRead::lines(reader).take(lines) {
+            let line = line.with_context(|| format!("Failed to read file: {}", path))?;
             println!("{}", line);
         }
         println!();
@@ -1626,20 +1643,53 @@ Always explain WHAT the command does and WHY it's needed.
         let editor = std::env::var("EDITOR").unwrap_or_else(|_| "vi".to_string());
         println!("  {} Opening {} in {}...", "→".truecolor(222, 115, 86), remote_path, editor);
 
-        let status = std::process::Command::new(&editor)
-            .arg(&temp_file)
-            .status()
-            .with_context(|| format!("Failed to launch editor: {}", editor))?;
+        loop {
+            let status = std::process::Command::new(&editor)
+                .arg(&temp_file)
+                .status()
+                .with_context(|| format!("Failed to launch editor: {}", editor))?;
+
+            if !status.success() {
+                println!("  {} Edit cancelled", "⚠".truecolor(222, 115, 86));
+                break;
+            }
 
-        if status.success() {
-            // Upload modified file
             let modified_content = std::fs::read(&temp_file)?;
+
+            // Refuse to upload syntax errors the edit introduced
+            if let Some(kind) = guestkit::lint::FileKind::detect(remote_path) {
+                let errors = guestkit::lint::new_errors(
+                    kind,
+                    &String::from_utf8_lossy(&content),
+                    &String::from_utf8_lossy(&modified_content),
+                );
+                if !errors.is_empty() {
+                    println!("  {} The edit introduces {} error(s):", "✗".red().bold(), kind);
+                    for error in &errors {
+                        println!("    {}", error);
+                    }
+                    print!("  Re-edit, save anyway or discard? [E/s/d] ");
+                    std::io::Write::flush(&mut std::io::stdout())?;
+
+                    let mut answer = String::new();
+                    std::io::stdin().read_line(&mut answer)?;
+                    match answer.trim().to_lowercase().as_str() {
+                        "s" | "save" => {}
+                        "d" | "discard" => {
+                            println!("  {} Edit discarded", "⚠".truecolor(222, 115, 86));
+                            break;
+                        }
+                        _ => continue,
+                    }
+                }
+            }
+
+            // Upload modified file
             self.handle.write(remote_path, &modified_content)
                 .with_context(|| format!("Failed to write back to VM: {}", remote_path))?;
 
             println!("  {} File updated in VM", "✓".truecolor(222, 115, 86).bold());
-        } else {
-            println!("  {} Edit cancelled", "⚠".truecolor(222, 115, 86));
+            break;
         }
 
         // Cleanup
@@ -0,0 +1,172 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Signed SBOM attestations, in the form `cosign attest-blob` writes
+//!
+//! The SBOM becomes the predicate of an in-toto statement about the disk
+//! image, in a DSSE envelope signed by the host's `openssl` with an EC or
+//! RSA private key in PEM. `cosign verify-blob-attestation --key <public
+//! key> --type <cyclonedx|spdxjson> --signature <envelope> <image>` checks
+//! it.
+
+use super::SbomFormat;
+use anyhow::{bail, Context, Result};
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+pub const PAYLOAD_TYPE: &str = "application/vnd.in-toto+json";
+pub const STATEMENT_TYPE: &str = "https://in-toto.io/Statement/v1";
+
+/// in-toto statement about the disk image
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Statement {
+    #[serde(rename = "_type")]
+    pub statement_type: String,
+    pub subject: Vec<Subject>,
+    #[serde(rename = "predicateType")]
+    pub predicate_type: String,
+    pub predicate: serde_json::Value,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Subject {
+    pub name: String,
+    pub digest: BTreeMap<String, String>,
+}
+
+/// DSSE envelope
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Envelope {
+    pub payload_type: String,
+    /// The statement, base64
+    pub payload: String,
+    pub signatures: Vec<Signature>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Signature {
+    pub keyid: String,
+    /// Signature of the pre-authentication encoding, base64
+    pub sig: String,
+}
+
+/// Predicate type of an SBOM format, as cosign's `--type` names them
+pub fn predicate_type(format: SbomFormat) -> Option<&'static str> {
+    match format {
+        SbomFormat::CycloneDx => Some("https://cyclonedx.org/bom"),
+        SbomFormat::Spdx | SbomFormat::Spdx3 => Some("https://spdx.dev/Document"),
+        SbomFormat::CycloneDxXml | SbomFormat::Json | SbomFormat::Csv => None,
+    }
+}
+
+/// SHA-256 of the disk image, hex
+pub fn image_digest(image: &Path) -> Result<String> {
+    let mut file =
+        File::open(image).with_context(|| format!("Failed to open {}", image.display()))?;
+    let mut hasher = Sha256::new();
+    std::io::copy(&mut file, &mut hasher)?;
+    Ok(format!("{:x}", hasher.finalize()))
+}
+
+/// The statement that `sbom` describes the image `name` with `digest`
+pub fn statement(name: &str, digest: &str, format: SbomFormat, sbom: &str) -> Result<Statement> {
+    let Some(predicate_type) = predicate_type(format) else {
+        bail!("Only SPDX and CycloneDX JSON SBOMs can be attested");
+    };
+    Ok(Statement {
+        statement_type: STATEMENT_TYPE.to_string(),
+        subject: vec![Subject {
+            name: name.to_string(),
+            digest: BTreeMap::from([("sha256".to_string(), digest.to_string())]),
+        }],
+        predicate_type: predicate_type.to_string(),
+        predicate: serde_json::from_str(sbom)?,
+    })
+}
+
+/// DSSE pre-authentication encoding, what is actually signed
+fn pae(payload_type: &str, payload: &[u8]) -> Vec<u8> {
+    let mut encoded = format!(
+        "DSSEv1 {} {} {} ",
+        payload_type.len(),
+        payload_type,
+        payload.len()
+    )
+    .into_bytes();
+    encoded.extend_from_slice(payload);
+    encoded
+}
+
+/// Sign a statement with a PEM private key
+pub fn sign(statement: &Statement, key: &Path) -> Result<Envelope> {
+    let payload = serde_json::to_vec(statement)?;
+
+    let mut openssl = Command::new("openssl")
+        .args(["dgst", "-sha256", "-sign"])
+        .arg(key)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .context("failed to run openssl")?;
+    openssl
+        .stdin
+        .take()
+        .context("openssl stdin")?
+        .write_all(&pae(PAYLOAD_TYPE, &payload))?;
+    let output = openssl.wait_with_output()?;
+    if !output.status.success() {
+        bail!(
+            "openssl could not sign with {}: {}",
+            key.display(),
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(Envelope {
+        payload_type: PAYLOAD_TYPE.to_string(),
+        payload: base64::engine::general_purpose::STANDARD.encode(payload),
+        signatures: vec![Signature {
+            keyid: String::new(),
+            sig: base64::engine::general_purpose::STANDARD.encode(output.stdout),
+        }],
+    })
+}
+
+/// Where the attestation of the SBOM at `sbom_path` goes
+pub fn attestation_path(sbom_path: &str) -> String {
+    format!("{}.intoto.jsonl", sbom_path)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_pae() {
+        // The example of the DSSE protocol description
+        assert_eq!(
+            pae("http://example.com/HelloWorld", b"hello world"),
+            b"DSSEv1 29 http://example.com/HelloWorld 11 hello world"
+        );
+    }
+
+    #[test]
+    fn test_statement() {
+        let sbom = r#"{"bomFormat": "CycloneDX", "specVersion": "1.6"}"#;
+        let attested = statement("web.qcow2", "abc123", SbomFormat::CycloneDx, sbom).unwrap();
+        let json = serde_json::to_value(&attested).unwrap();
+        assert_eq!(json["_type"], STATEMENT_TYPE);
+        assert_eq!(json["subject"][0]["name"], "web.qcow2");
+        assert_eq!(json["subject"][0]["digest"]["sha256"], "abc123");
+        assert_eq!(json["predicateType"], "https://cyclonedx.org/bom");
+        assert_eq!(json["predicate"]["specVersion"], "1.6");
+
+        assert!(statement("web.qcow2", "abc123", SbomFormat::Csv, "a,b").is_err());
+    }
+}
@@ -1,61 +1,95 @@
 // SPDX-License-Identifier: LGPL-3.0-or-later
-//! CVE vulnerability lookup
+//! CVE lookup against the offline vulnerability database
 
 use super::VulnerabilityInfo;
-use anyhow::Result;
-use std::collections::HashMap;
-use once_cell::sync::Lazy;
+use crate::cli::vulndb::{self, VulnDb};
+use guestkit::guestfs::Application;
+use guestkit::Guestfs;
 
-/// Known CVEs for demonstration (in production, this would query a CVE database)
-static KNOWN_CVES: Lazy<HashMap<&'static str, Vec<(&'static str, &'static str, f64)>>> = Lazy::new(|| {
-    let mut m: HashMap<&'static str, Vec<(&'static str, &'static str, f64)>> = HashMap::new();
-
-    // Example CVEs (package_name -> [(cve_id, severity, score)])
-    m.insert("openssl", vec![
-        ("CVE-2024-0727", "high", 7.5),
-        ("CVE-2023-6129", "medium", 5.3),
-    ]);
-
-    m.insert("nginx", vec![
-        ("CVE-2023-44487", "high", 7.5),
-    ]);
-
-    m.insert("curl", vec![
-        ("CVE-2023-46218", "medium", 6.5),
-    ]);
+/// Looks up the packages of one distro release
+pub struct CveLookup<'a> {
+    db: &'a VulnDb,
+    ecosystem: String,
+}
 
-    m.insert("python3", vec![
-        ("CVE-2023-40217", "medium", 5.3),
-    ]);
+impl<'a> CveLookup<'a> {
+    pub fn new(db: &'a VulnDb, ecosystem: String) -> Self {
+        Self { db, ecosystem }
+    }
 
-    m
-});
+    /// Lookups for the operating system at `root`, if the database covers
+    /// its release
+    pub fn for_os(db: &'a VulnDb, g: &mut Guestfs, root: &str) -> Option<Self> {
+        let Some(ecosystem) = vulndb::os_ecosystem(g, root) else {
+            log::warn!(
+                "No vulnerability feed covers the operating system at {}",
+                root
+            );
+            return None;
+        };
+        if !db.covers(&ecosystem) {
+            log::warn!(
+                "The vulnerability database has no advisories for {}; run `guestctl vulndb update`",
+                ecosystem
+            );
+            return None;
+        }
+        Some(Self::new(db, ecosystem))
+    }
 
-/// Lookup CVEs for a package
-pub fn lookup_cves(package_name: &str, package_version: &str) -> Result<Vec<VulnerabilityInfo>> {
-    let mut vulnerabilities = Vec::new();
+    /// Known vulnerabilities of an installed package, one per CVE, most
+    /// severe first
+    ///
+    /// Debian, Ubuntu and Alpine publish advisories for source packages, so
+    /// the source package is looked up as well as the binary one.
+    pub fn lookup(&self, app: &Application) -> Vec<VulnerabilityInfo> {
+        let version = app.full_version();
+        let mut names = vec![app.name.as_str()];
+        if !app.source.is_empty() && app.source != app.name {
+            names.insert(0, app.source.as_str());
+        }
 
-    // Check if we have known CVEs for this package
-    if let Some(cves) = KNOWN_CVES.get(package_name) {
-        for (cve_id, severity, score) in cves {
-            vulnerabilities.push(VulnerabilityInfo {
-                cve: cve_id.to_string(),
-                severity: severity.to_string(),
-                score: Some(*score),
-                description: format!(
-                    "Vulnerability in {} {}",
-                    package_name, package_version
-                ),
-                fixed_version: None,
-            });
+        let mut vulnerabilities: Vec<VulnerabilityInfo> = Vec::new();
+        for name in names {
+            for finding in self.db.lookup(&self.ecosystem, name, &version) {
+                let advisory = finding.advisory;
+                let cves = if advisory.cves.is_empty() {
+                    vec![advisory.id.clone()]
+                } else {
+                    advisory.cves.clone()
+                };
+                for cve in cves {
+                    if vulnerabilities.iter().any(|v| v.cve == cve) {
+                        continue;
+                    }
+                    vulnerabilities.push(VulnerabilityInfo {
+                        cve,
+                        severity: advisory
+                            .severity
+                            .clone()
+                            .unwrap_or_else(|| "unknown".to_string()),
+                        score: advisory.score,
+                        description: if advisory.summary.is_empty() {
+                            advisory.id.clone()
+                        } else {
+                            advisory.summary.clone()
+                        },
+                        fixed_version: finding.fixed.map(str::to_string),
+                    });
+                }
+            }
         }
-    }
 
-    Ok(vulnerabilities)
+        vulnerabilities.sort_by(|a, b| {
+            severity_rank(&b.severity)
+                .cmp(&severity_rank(&a.severity))
+                .then_with(|| a.cve.cmp(&b.cve))
+        });
+        vulnerabilities
+    }
 }
 
 /// Filter vulnerabilities by severity
-#[allow(dead_code)]
 pub fn filter_by_severity(
     vulnerabilities: &[VulnerabilityInfo],
     min_severity: &str,
@@ -69,8 +103,7 @@ pub fn filter_by_severity(
         .collect()
 }
 
-#[allow(dead_code)]
-fn severity_rank(severity: &str) -> u8 {
+pub fn severity_rank(severity: &str) -> u8 {
     match severity.to_lowercase().as_str() {
         "critical" => 4,
         "high" => 3,
@@ -79,3 +112,74 @@ fn severity_rank(severity: &str) -> u8 {
         _ => 0,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::vulndb::version::Scheme;
+    use crate::cli::vulndb::{Advisory, AffectedPackage, Range, Record};
+
+    fn advisory(id: &str, cves: &[&str], severity: &str, package: &str, fixed: &str) -> Record {
+        Record {
+            advisory: Advisory {
+                id: id.to_string(),
+                cves: cves.iter().map(|c| c.to_string()).collect(),
+                severity: Some(severity.to_string()),
+                score: None,
+                summary: format!("{} issue", package),
+            },
+            packages: vec![AffectedPackage {
+                ecosystem: "Debian:12".to_string(),
+                name: package.to_string(),
+                scheme: Scheme::Dpkg,
+                ranges: vec![Range {
+                    introduced: Some("0".to_string()),
+                    fixed: Some(fixed.to_string()),
+                    last_affected: None,
+                }],
+                versions: Vec::new(),
+            }],
+        }
+    }
+
+    #[test]
+    fn test_lookup_source_package() {
+        let mut db = VulnDb::default();
+        db.add(advisory(
+            "DSA-5621-1",
+            &["CVE-2024-0727", "CVE-2023-6129"],
+            "medium",
+            "openssl",
+            "3.0.13-1~deb12u1",
+        ));
+        db.add(advisory(
+            "DEBIAN-CVE-2024-0727",
+            &["CVE-2024-0727"],
+            "low",
+            "openssl",
+            "3.0.13-1~deb12u1",
+        ));
+        db.add(advisory(
+            "CVE-2024-9999",
+            &[],
+            "critical",
+            "libssl3",
+            "3.0.14-1",
+        ));
+
+        let app = Application {
+            name: "libssl3".to_string(),
+            version: "3.0.11".to_string(),
+            release: "1~deb12u2+b1".to_string(),
+            source: "openssl".to_string(),
+            ..Default::default()
+        };
+        let lookup = CveLookup::new(&db, "Debian:12".to_string());
+        let found = lookup.lookup(&app);
+        let cves: Vec<_> = found.iter().map(|v| v.cve.as_str()).collect();
+        assert_eq!(cves, ["CVE-2024-9999", "CVE-2023-6129", "CVE-2024-0727"]);
+        assert_eq!(found[1].fixed_version.as_deref(), Some("3.0.13-1~deb12u1"));
+        assert_eq!(found[2].severity, "medium");
+        assert_eq!(filter_by_severity(&found, "high").len(), 1);
+    }
+}
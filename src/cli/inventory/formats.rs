@@ -1,11 +1,17 @@
 // SPDX-License-Identifier: LGPL-3.0-or-later
 //! SBOM format converters (SPDX, CycloneDX, CSV)
 
-use super::Inventory;
+use super::{ContainerImageInfo, Inventory, PackageInfo};
+use crate::cli::vulndb::vex;
 use anyhow::Result;
+use guestkit::core::escape::xml_escape;
 use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
 use uuid::Uuid;
 
+pub const CYCLONEDX_VERSION: &str = "1.6";
+pub const SPDX3_VERSION: &str = "3.0.1";
+
 /// SPDX 2.3 Document
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
@@ -54,10 +60,132 @@ pub struct SpdxRelationship {
     pub related_spdx_element: String,
 }
 
-/// CycloneDX 1.5 BOM
+/// SPDX 3.0 document, as JSON-LD
+#[derive(Debug, Serialize, Deserialize)]
+pub struct Spdx3Document {
+    #[serde(rename = "@context")]
+    pub context: String,
+    #[serde(rename = "@graph")]
+    pub graph: Vec<Spdx3Element>,
+}
+
+/// An element of the SPDX 3.0 graph; all but `CreationInfo` share the
+/// document's creation info by its blank node ID
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type")]
+pub enum Spdx3Element {
+    CreationInfo(Spdx3CreationInfo),
+    SoftwareAgent(Spdx3Agent),
+    Tool(Spdx3Agent),
+    SpdxDocument(Spdx3Collection),
+    #[serde(rename = "software_Sbom")]
+    Sbom(Spdx3Collection),
+    #[serde(rename = "software_Package")]
+    Package(Spdx3Package),
+    #[serde(rename = "simplelicensing_LicenseExpression")]
+    LicenseExpression(Spdx3LicenseExpression),
+    #[serde(rename = "security_Vulnerability")]
+    Vulnerability(Spdx3Vulnerability),
+    Relationship(Spdx3Relationship),
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Spdx3CreationInfo {
+    #[serde(rename = "@id")]
+    pub id: String,
+    pub spec_version: String,
+    pub created: String,
+    pub created_by: Vec<String>,
+    pub created_using: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Spdx3Agent {
+    pub spdx_id: String,
+    pub creation_info: String,
+    pub name: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Spdx3Collection {
+    pub spdx_id: String,
+    pub creation_info: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub profile_conformance: Vec<String>,
+    #[serde(
+        rename = "software_sbomType",
+        default,
+        skip_serializing_if = "Vec::is_empty"
+    )]
+    pub sbom_type: Vec<String>,
+    pub root_element: Vec<String>,
+    pub element: Vec<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Spdx3Package {
+    pub spdx_id: String,
+    pub creation_info: String,
+    pub name: String,
+    #[serde(rename = "software_packageVersion")]
+    pub package_version: String,
+    #[serde(rename = "software_packageUrl")]
+    pub package_url: String,
+    #[serde(rename = "software_primaryPurpose")]
+    pub primary_purpose: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Spdx3LicenseExpression {
+    pub spdx_id: String,
+    pub creation_info: String,
+    #[serde(rename = "simplelicensing_licenseExpression")]
+    pub license_expression: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Spdx3Vulnerability {
+    pub spdx_id: String,
+    pub creation_info: String,
+    pub name: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub description: Option<String>,
+    pub external_identifier: Vec<Spdx3ExternalIdentifier>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Spdx3ExternalIdentifier {
+    #[serde(rename = "type")]
+    pub identifier_kind: String,
+    pub external_identifier_type: String,
+    pub identifier: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Spdx3Relationship {
+    pub spdx_id: String,
+    pub creation_info: String,
+    pub from: String,
+    pub relationship_type: String,
+    pub to: Vec<String>,
+}
+
+/// CycloneDX 1.6 BOM
 #[derive(Debug, Serialize, Deserialize)]
 #[serde(rename_all = "camelCase")]
 pub struct CycloneDxBom {
+    #[serde(rename = "$schema")]
+    pub schema: String,
     pub bom_format: String,
     pub spec_version: String,
     pub serial_number: String,
@@ -71,15 +199,14 @@ pub struct CycloneDxBom {
 #[derive(Debug, Serialize, Deserialize)]
 pub struct CdxMetadata {
     pub timestamp: String,
-    pub tools: Vec<CdxTool>,
+    pub tools: CdxTools,
     pub component: CdxRootComponent,
 }
 
+/// Tools as components, which replaced the tool list in CycloneDX 1.5
 #[derive(Debug, Serialize, Deserialize)]
-pub struct CdxTool {
-    pub vendor: String,
-    pub name: String,
-    pub version: String,
+pub struct CdxTools {
+    pub components: Vec<CdxRootComponent>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -96,6 +223,7 @@ pub struct CdxRootComponent {
 pub struct CdxComponent {
     #[serde(rename = "type")]
     pub component_type: String,
+    #[serde(rename = "bom-ref")]
     pub bom_ref: String,
     pub name: String,
     pub version: String,
@@ -103,6 +231,9 @@ pub struct CdxComponent {
     pub purl: Option<String>,
     #[serde(skip_serializing_if = "Vec::is_empty")]
     pub licenses: Vec<CdxLicense>,
+    /// What a container image holds
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub components: Vec<CdxComponent>,
 }
 
 #[derive(Debug, Serialize, Deserialize)]
@@ -148,11 +279,7 @@ pub struct CdxAffect {
 /// Convert inventory to SPDX format
 pub fn to_spdx(inventory: &Inventory) -> Result<SpdxDocument> {
     let doc_id = format!("SPDXRef-DOCUMENT");
-    let namespace = format!(
-        "https://guestkit.dev/sbom/{}/{}",
-        inventory.image_path.replace('/', "-"),
-        chrono::Utc::now().format("%Y%m%d-%H%M%S")
-    );
+    let namespace = document_namespace(inventory);
 
     let mut packages = Vec::new();
     let mut relationships = Vec::new();
@@ -178,6 +305,49 @@ pub fn to_spdx(inventory: &Inventory) -> Result<SpdxDocument> {
         });
     }
 
+    // Container images, each containing its own packages
+    for (idx, image) in inventory.containers.iter().enumerate() {
+        let image_id = format!("SPDXRef-Container-{}", idx);
+
+        packages.push(SpdxPackage {
+            spdxid: image_id.clone(),
+            name: image.name().to_string(),
+            version_info: Some(image.id.clone()),
+            download_location: "NOASSERTION".to_string(),
+            files_analyzed: false,
+            license_concluded: None,
+            license_declared: None,
+            copyright_text: "NOASSERTION".to_string(),
+        });
+
+        relationships.push(SpdxRelationship {
+            spdx_element_id: doc_id.clone(),
+            relationship_type: "DESCRIBES".to_string(),
+            related_spdx_element: image_id.clone(),
+        });
+
+        for (pkg_idx, pkg) in image.packages.iter().enumerate() {
+            let pkg_id = format!("{}-Package-{}", image_id, pkg_idx);
+
+            packages.push(SpdxPackage {
+                spdxid: pkg_id.clone(),
+                name: pkg.name.clone(),
+                version_info: Some(pkg.version.clone()),
+                download_location: "NOASSERTION".to_string(),
+                files_analyzed: false,
+                license_concluded: pkg.license.clone(),
+                license_declared: pkg.license.clone(),
+                copyright_text: "NOASSERTION".to_string(),
+            });
+
+            relationships.push(SpdxRelationship {
+                spdx_element_id: image_id.clone(),
+                relationship_type: "CONTAINS".to_string(),
+                related_spdx_element: pkg_id,
+            });
+        }
+    }
+
     Ok(SpdxDocument {
         spdx_version: "SPDX-2.3".to_string(),
         data_license: "CC0-1.0".to_string(),
@@ -194,6 +364,225 @@ pub fn to_spdx(inventory: &Inventory) -> Result<SpdxDocument> {
     })
 }
 
+/// Unique namespace of an SPDX document's IDs
+fn document_namespace(inventory: &Inventory) -> String {
+    format!(
+        "https://guestkit.dev/sbom/{}/{}",
+        inventory.image_path.replace('/', "-"),
+        chrono::Utc::now().format("%Y%m%d-%H%M%S")
+    )
+}
+
+/// Collects the elements of an SPDX 3.0 SBOM
+struct Spdx3Builder {
+    namespace: String,
+    graph: Vec<Spdx3Element>,
+    /// IDs of the elements the SBOM holds
+    elements: Vec<String>,
+    /// License expression and vulnerability elements, shared by packages
+    licenses: BTreeMap<String, String>,
+    vulnerabilities: BTreeMap<String, String>,
+}
+
+impl Spdx3Builder {
+    const CREATION_INFO: &'static str = "_:creationinfo";
+
+    fn id(&self, kind: &str) -> String {
+        format!("{}#{}-{}", self.namespace, kind, self.elements.len())
+    }
+
+    fn add(&mut self, id: String, element: Spdx3Element) {
+        self.elements.push(id);
+        self.graph.push(element);
+    }
+
+    fn relate(&mut self, from: &str, relationship_type: &str, to: Vec<String>) {
+        let spdx_id = self.id("Relationship");
+        let relationship = Spdx3Relationship {
+            spdx_id: spdx_id.clone(),
+            creation_info: Self::CREATION_INFO.to_string(),
+            from: from.to_string(),
+            relationship_type: relationship_type.to_string(),
+            to,
+        };
+        self.add(spdx_id, Spdx3Element::Relationship(relationship));
+    }
+
+    /// Add a package with its license and vulnerabilities, returning its ID
+    fn package(&mut self, pkg: &PackageInfo, purl: String, purpose: &str) -> String {
+        let spdx_id = self.id("Package");
+        let package = Spdx3Package {
+            spdx_id: spdx_id.clone(),
+            creation_info: Self::CREATION_INFO.to_string(),
+            name: pkg.name.clone(),
+            package_version: pkg.version.clone(),
+            package_url: purl,
+            primary_purpose: purpose.to_string(),
+        };
+        self.add(spdx_id.clone(), Spdx3Element::Package(package));
+
+        if let Some(license) = &pkg.license {
+            let license_id = match self.licenses.get(license) {
+                Some(id) => id.clone(),
+                None => {
+                    let id = self.id("License");
+                    let expression = Spdx3LicenseExpression {
+                        spdx_id: id.clone(),
+                        creation_info: Self::CREATION_INFO.to_string(),
+                        license_expression: license.clone(),
+                    };
+                    self.add(id.clone(), Spdx3Element::LicenseExpression(expression));
+                    self.licenses.insert(license.clone(), id.clone());
+                    id
+                }
+            };
+            self.relate(&spdx_id, "hasConcludedLicense", vec![license_id]);
+        }
+
+        let mut vulnerabilities = Vec::new();
+        for vuln in &pkg.vulnerabilities {
+            let vuln_id = match self.vulnerabilities.get(&vuln.cve) {
+                Some(id) => id.clone(),
+                None => {
+                    let id = self.id("Vulnerability");
+                    let external_identifier = if vuln.cve.starts_with("CVE-") {
+                        vec![Spdx3ExternalIdentifier {
+                            identifier_kind: "ExternalIdentifier".to_string(),
+                            external_identifier_type: "cve".to_string(),
+                            identifier: vuln.cve.clone(),
+                        }]
+                    } else {
+                        Vec::new()
+                    };
+                    let vulnerability = Spdx3Vulnerability {
+                        spdx_id: id.clone(),
+                        creation_info: Self::CREATION_INFO.to_string(),
+                        name: vuln.cve.clone(),
+                        description: Some(vuln.description.clone())
+                            .filter(|description| !description.is_empty()),
+                        external_identifier,
+                    };
+                    self.add(id.clone(), Spdx3Element::Vulnerability(vulnerability));
+                    self.vulnerabilities.insert(vuln.cve.clone(), id.clone());
+                    id
+                }
+            };
+            vulnerabilities.push(vuln_id);
+        }
+        if !vulnerabilities.is_empty() {
+            self.relate(&spdx_id, "hasAssociatedVulnerability", vulnerabilities);
+        }
+
+        spdx_id
+    }
+}
+
+/// Convert inventory to SPDX 3.0 format
+///
+/// Licenses and vulnerabilities are elements of their own, tied to the
+/// packages by relationships.
+pub fn to_spdx3(inventory: &Inventory) -> Result<Spdx3Document> {
+    let mut builder = Spdx3Builder {
+        namespace: document_namespace(inventory),
+        graph: Vec::new(),
+        elements: Vec::new(),
+        licenses: BTreeMap::new(),
+        vulnerabilities: BTreeMap::new(),
+    };
+
+    let mut roots = Vec::new();
+    for pkg in &inventory.packages {
+        let purl = package_purl(inventory, pkg);
+        roots.push(builder.package(pkg, purl, "library"));
+    }
+
+    // Container images, each containing its own packages
+    for image in &inventory.containers {
+        let image_pkg = PackageInfo {
+            name: image.name().to_string(),
+            version: image.id.clone(),
+            package_type: "oci".to_string(),
+            license: None,
+            size: None,
+            installed_date: None,
+            files: Vec::new(),
+            dependencies: Vec::new(),
+            vulnerabilities: Vec::new(),
+            checksum: None,
+        };
+        let image_id = builder.package(&image_pkg, oci_purl(image), "container");
+        roots.push(image_id.clone());
+
+        let mut contained = Vec::new();
+        for pkg in &image.packages {
+            let purl = vex::package_purl(&pkg.package_type, "", &pkg.name, &pkg.version, "");
+            contained.push(builder.package(pkg, purl, "library"));
+        }
+        if !contained.is_empty() {
+            builder.relate(&image_id, "contains", contained);
+        }
+    }
+
+    let namespace = &builder.namespace;
+    let agent = format!("{}#guestkit", namespace);
+    let tool = format!("{}#guestkit-{}", namespace, env!("CARGO_PKG_VERSION"));
+    let sbom = format!("{}#SBOM", namespace);
+    let document = format!("{}#DOCUMENT", namespace);
+    let creation_info = Spdx3Builder::CREATION_INFO.to_string();
+
+    let mut profiles = vec!["core", "software"];
+    if !builder.licenses.is_empty() {
+        profiles.push("simpleLicensing");
+    }
+    if !builder.vulnerabilities.is_empty() {
+        profiles.push("security");
+    }
+
+    let mut graph = vec![
+        Spdx3Element::CreationInfo(Spdx3CreationInfo {
+            id: creation_info.clone(),
+            spec_version: SPDX3_VERSION.to_string(),
+            created: inventory.scanned_at.clone(),
+            created_by: vec![agent.clone()],
+            created_using: vec![tool.clone()],
+        }),
+        Spdx3Element::SoftwareAgent(Spdx3Agent {
+            spdx_id: agent.clone(),
+            creation_info: creation_info.clone(),
+            name: "guestkit".to_string(),
+        }),
+        Spdx3Element::Tool(Spdx3Agent {
+            spdx_id: tool.clone(),
+            creation_info: creation_info.clone(),
+            name: format!("guestkit-{}", env!("CARGO_PKG_VERSION")),
+        }),
+        Spdx3Element::SpdxDocument(Spdx3Collection {
+            spdx_id: document,
+            creation_info: creation_info.clone(),
+            name: Some(inventory.image_path.clone()),
+            profile_conformance: profiles.into_iter().map(str::to_string).collect(),
+            sbom_type: Vec::new(),
+            root_element: vec![sbom.clone()],
+            element: vec![sbom.clone(), agent, tool],
+        }),
+        Spdx3Element::Sbom(Spdx3Collection {
+            spdx_id: sbom,
+            creation_info,
+            name: None,
+            profile_conformance: Vec::new(),
+            sbom_type: vec!["analyzed".to_string()],
+            root_element: roots,
+            element: builder.elements,
+        }),
+    ];
+    graph.extend(builder.graph);
+
+    Ok(Spdx3Document {
+        context: format!("https://spdx.org/rdf/{}/spdx-context.jsonld", SPDX3_VERSION),
+        graph,
+    })
+}
+
 /// Convert inventory to CycloneDX format
 pub fn to_cyclonedx(inventory: &Inventory) -> Result<CycloneDxBom> {
     let serial_number = format!("urn:uuid:{}", Uuid::new_v4());
@@ -202,23 +591,7 @@ pub fn to_cyclonedx(inventory: &Inventory) -> Result<CycloneDxBom> {
     let mut vulnerabilities = Vec::new();
 
     for pkg in &inventory.packages {
-        let bom_ref = format!(
-            "pkg:{}/{}/{}@{}",
-            pkg.package_type,
-            inventory.os_name.to_lowercase(),
-            pkg.name,
-            pkg.version
-        );
-
-        let licenses = if let Some(license) = &pkg.license {
-            vec![CdxLicense {
-                license: CdxLicenseChoice {
-                    id: license.clone(),
-                },
-            }]
-        } else {
-            Vec::new()
-        };
+        let bom_ref = package_purl(inventory, pkg);
 
         components.push(CdxComponent {
             component_type: "library".to_string(),
@@ -226,41 +599,62 @@ pub fn to_cyclonedx(inventory: &Inventory) -> Result<CycloneDxBom> {
             name: pkg.name.clone(),
             version: pkg.version.clone(),
             purl: Some(bom_ref.clone()),
-            licenses,
+            licenses: cdx_licenses(pkg),
+            components: Vec::new(),
         });
 
-        // Add vulnerabilities
-        for vuln in &pkg.vulnerabilities {
-            vulnerabilities.push(CdxVulnerability {
-                id: vuln.cve.clone(),
-                source: CdxSource {
-                    name: "NVD".to_string(),
-                    url: format!("https://nvd.nist.gov/vuln/detail/{}", vuln.cve),
-                },
-                ratings: vec![CdxRating {
-                    severity: vuln.severity.clone(),
-                    score: vuln.score,
-                    method: "CVSSv3".to_string(),
-                }],
-                affects: vec![CdxAffect {
-                    component_ref: bom_ref.clone(),
-                }],
+        add_cdx_vulnerabilities(pkg, &bom_ref, &mut vulnerabilities);
+    }
+
+    // Container images, with their packages nested inside
+    for image in &inventory.containers {
+        let image_purl = oci_purl(image);
+        let mut nested = Vec::new();
+
+        for pkg in &image.packages {
+            let purl = vex::package_purl(&pkg.package_type, "", &pkg.name, &pkg.version, "");
+            // The same package may be in several images
+            let bom_ref = format!("{}#{}", image_purl, purl);
+
+            nested.push(CdxComponent {
+                component_type: "library".to_string(),
+                bom_ref: bom_ref.clone(),
+                name: pkg.name.clone(),
+                version: pkg.version.clone(),
+                purl: Some(purl),
+                licenses: cdx_licenses(pkg),
+                components: Vec::new(),
             });
+
+            add_cdx_vulnerabilities(pkg, &bom_ref, &mut vulnerabilities);
         }
+
+        components.push(CdxComponent {
+            component_type: "container".to_string(),
+            bom_ref: image_purl.clone(),
+            name: image.name().to_string(),
+            version: image.id.clone(),
+            purl: Some(image_purl),
+            licenses: Vec::new(),
+            components: nested,
+        });
     }
 
     Ok(CycloneDxBom {
+        schema: format!("http://cyclonedx.org/schema/bom-{}.schema.json", CYCLONEDX_VERSION),
         bom_format: "CycloneDX".to_string(),
-        spec_version: "1.5".to_string(),
+        spec_version: CYCLONEDX_VERSION.to_string(),
         serial_number,
         version: 1,
         metadata: CdxMetadata {
             timestamp: inventory.scanned_at.clone(),
-            tools: vec![CdxTool {
-                vendor: "guestkit".to_string(),
-                name: "guestkit".to_string(),
-                version: env!("CARGO_PKG_VERSION").to_string(),
-            }],
+            tools: CdxTools {
+                components: vec![CdxRootComponent {
+                    component_type: "application".to_string(),
+                    name: "guestkit".to_string(),
+                    version: env!("CARGO_PKG_VERSION").to_string(),
+                }],
+            },
             component: CdxRootComponent {
                 component_type: "application".to_string(),
                 name: inventory.image_path.clone(),
@@ -272,6 +666,212 @@ pub fn to_cyclonedx(inventory: &Inventory) -> Result<CycloneDxBom> {
     })
 }
 
+fn cdx_licenses(pkg: &PackageInfo) -> Vec<CdxLicense> {
+    match &pkg.license {
+        Some(license) => vec![CdxLicense {
+            license: CdxLicenseChoice {
+                id: license.clone(),
+            },
+        }],
+        None => Vec::new(),
+    }
+}
+
+fn add_cdx_vulnerabilities(
+    pkg: &PackageInfo,
+    bom_ref: &str,
+    vulnerabilities: &mut Vec<CdxVulnerability>,
+) {
+    for vuln in &pkg.vulnerabilities {
+        vulnerabilities.push(CdxVulnerability {
+            id: vuln.cve.clone(),
+            source: CdxSource {
+                name: "NVD".to_string(),
+                url: format!("https://nvd.nist.gov/vuln/detail/{}", vuln.cve),
+            },
+            ratings: vec![CdxRating {
+                severity: vuln.severity.clone(),
+                score: vuln.score,
+                method: if vuln.score.is_some() { "CVSSv3" } else { "other" }.to_string(),
+            }],
+            affects: vec![CdxAffect {
+                component_ref: bom_ref.to_string(),
+            }],
+        });
+    }
+}
+
+/// Package URL of a container image,
+/// `pkg:oci/<name>@<digest>?repository_url=<repository>`
+///
+/// The version is the manifest digest if the image was pulled by one, the
+/// image ID otherwise.
+fn oci_purl(image: &ContainerImageInfo) -> String {
+    let pinned = image
+        .digests
+        .first()
+        .and_then(|reference| reference.split_once('@'));
+    let (repository, digest, tag) = match (pinned, image.tags.first()) {
+        (Some((repository, digest)), _) => (Some(repository), digest, None),
+        (None, Some(tag)) => {
+            // A colon after the last slash starts the tag; one before is a port
+            match tag.rfind(':').filter(|&colon| !tag[colon..].contains('/')) {
+                Some(colon) => (
+                    Some(&tag[..colon]),
+                    image.id.as_str(),
+                    Some(&tag[colon + 1..]),
+                ),
+                None => (Some(tag.as_str()), image.id.as_str(), None),
+            }
+        }
+        (None, None) => (None, image.id.as_str(), None),
+    };
+
+    let name = repository
+        .and_then(|repository| repository.rsplit('/').next())
+        .unwrap_or_else(|| image.name())
+        .to_lowercase();
+    let mut purl = format!("pkg:oci/{}@{}", name, digest.replace(':', "%3A"));
+    let mut qualifiers = Vec::new();
+    if let Some(repository) = repository {
+        qualifiers.push(format!("repository_url={}", repository));
+    }
+    if let Some(tag) = tag {
+        qualifiers.push(format!("tag={}", tag));
+    }
+    if !qualifiers.is_empty() {
+        purl = format!("{}?{}", purl, qualifiers.join("&"));
+    }
+    purl
+}
+
+/// Package URL of one of the guest's packages, namespaced by its distro
+fn package_purl(inventory: &Inventory, pkg: &PackageInfo) -> String {
+    vex::package_purl(&pkg.package_type, &inventory.distro, &pkg.name, &pkg.version, "")
+}
+
+/// Write a CycloneDX BOM as XML
+pub fn cyclonedx_xml(bom: &CycloneDxBom) -> String {
+    let mut xml = String::from("<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n");
+    xml.push_str(&format!(
+        "<bom xmlns=\"http://cyclonedx.org/schema/bom/{}\" serialNumber=\"{}\" version=\"{}\">\n",
+        bom.spec_version,
+        xml_escape(&bom.serial_number),
+        bom.version
+    ));
+
+    xml.push_str("  <metadata>\n");
+    xml.push_str(&format!(
+        "    <timestamp>{}</timestamp>\n",
+        xml_escape(&bom.metadata.timestamp)
+    ));
+    xml.push_str("    <tools>\n      <components>\n");
+    for tool in &bom.metadata.tools.components {
+        xml.push_str(&format!(
+            "        <component type=\"{}\">\n          <name>{}</name>\n          <version>{}</version>\n        </component>\n",
+            xml_escape(&tool.component_type),
+            xml_escape(&tool.name),
+            xml_escape(&tool.version)
+        ));
+    }
+    xml.push_str("      </components>\n    </tools>\n");
+    let root = &bom.metadata.component;
+    xml.push_str(&format!(
+        "    <component type=\"{}\">\n      <name>{}</name>\n      <version>{}</version>\n    </component>\n",
+        xml_escape(&root.component_type),
+        xml_escape(&root.name),
+        xml_escape(&root.version)
+    ));
+    xml.push_str("  </metadata>\n");
+
+    xml.push_str("  <components>\n");
+    for component in &bom.components {
+        cyclonedx_xml_component(&mut xml, component, 2);
+    }
+    xml.push_str("  </components>\n");
+
+    if !bom.vulnerabilities.is_empty() {
+        xml.push_str("  <vulnerabilities>\n");
+        for vuln in &bom.vulnerabilities {
+            xml.push_str("    <vulnerability>\n");
+            xml.push_str(&format!("      <id>{}</id>\n", xml_escape(&vuln.id)));
+            xml.push_str(&format!(
+                "      <source>\n        <name>{}</name>\n        <url>{}</url>\n      </source>\n",
+                xml_escape(&vuln.source.name),
+                xml_escape(&vuln.source.url)
+            ));
+            xml.push_str("      <ratings>\n");
+            for rating in &vuln.ratings {
+                xml.push_str("        <rating>\n");
+                if let Some(score) = rating.score {
+                    xml.push_str(&format!("          <score>{}</score>\n", score));
+                }
+                xml.push_str(&format!(
+                    "          <severity>{}</severity>\n          <method>{}</method>\n",
+                    xml_escape(&rating.severity),
+                    xml_escape(&rating.method)
+                ));
+                xml.push_str("        </rating>\n");
+            }
+            xml.push_str("      </ratings>\n");
+            xml.push_str("      <affects>\n");
+            for affect in &vuln.affects {
+                xml.push_str(&format!(
+                    "        <target>\n          <ref>{}</ref>\n        </target>\n",
+                    xml_escape(&affect.component_ref)
+                ));
+            }
+            xml.push_str("      </affects>\n");
+            xml.push_str("    </vulnerability>\n");
+        }
+        xml.push_str("  </vulnerabilities>\n");
+    }
+
+    xml.push_str("</bom>\n");
+    xml
+}
+
+/// Elements in the order the CycloneDX schema requires
+fn cyclonedx_xml_component(xml: &mut String, component: &CdxComponent, depth: usize) {
+    let indent = "  ".repeat(depth);
+    xml.push_str(&format!(
+        "{}<component type=\"{}\" bom-ref=\"{}\">\n",
+        indent,
+        xml_escape(&component.component_type),
+        xml_escape(&component.bom_ref)
+    ));
+    xml.push_str(&format!("{}  <name>{}</name>\n", indent, xml_escape(&component.name)));
+    xml.push_str(&format!(
+        "{}  <version>{}</version>\n",
+        indent,
+        xml_escape(&component.version)
+    ));
+    if !component.licenses.is_empty() {
+        xml.push_str(&format!("{}  <licenses>\n", indent));
+        for license in &component.licenses {
+            xml.push_str(&format!(
+                "{}    <license>\n{}      <id>{}</id>\n{}    </license>\n",
+                indent,
+                indent,
+                xml_escape(&license.license.id),
+                indent
+            ));
+        }
+        xml.push_str(&format!("{}  </licenses>\n", indent));
+    }
+    if let Some(purl) = &component.purl {
+        xml.push_str(&format!("{}  <purl>{}</purl>\n", indent, xml_escape(purl)));
+    }
+    if !component.components.is_empty() {
+        xml.push_str(&format!("{}  <components>\n", indent));
+        for nested in &component.components {
+            cyclonedx_xml_component(xml, nested, depth + 2);
+        }
+        xml.push_str(&format!("{}  </components>\n", indent));
+    }
+    xml.push_str(&format!("{}</component>\n", indent));
+}
+
 /// Convert inventory to CSV format
 pub fn to_csv(inventory: &Inventory) -> Result<String> {
     let mut csv = String::new();
@@ -332,3 +932,151 @@ fn severity_rank(severity: &str) -> u8 {
         _ => 0,
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::inventory::{InventoryStatistics, VulnerabilityInfo};
+
+    fn package(name: &str, version: &str, license: Option<&str>) -> PackageInfo {
+        PackageInfo {
+            name: name.to_string(),
+            version: version.to_string(),
+            package_type: "deb".to_string(),
+            license: license.map(str::to_string),
+            size: None,
+            installed_date: None,
+            files: Vec::new(),
+            dependencies: Vec::new(),
+            vulnerabilities: Vec::new(),
+            checksum: None,
+        }
+    }
+
+    fn inventory() -> Inventory {
+        let mut openssl = package("openssl", "3.0.11-1~deb12u2", Some("Apache-2.0"));
+        openssl.vulnerabilities.push(VulnerabilityInfo {
+            cve: "CVE-2024-0727".to_string(),
+            severity: "medium".to_string(),
+            score: Some(5.5),
+            description: "PKCS12 <NULL> dereference".to_string(),
+            fixed_version: Some("3.0.13-1~deb12u1".to_string()),
+        });
+        Inventory {
+            image_path: "/var/lib/images/web.qcow2".to_string(),
+            root: "/dev/sda1".to_string(),
+            scanned_at: "2024-03-01T10:00:00+00:00".to_string(),
+            os_name: "Debian GNU/Linux 12 (bookworm)".to_string(),
+            os_version: "12".to_string(),
+            distro: "debian".to_string(),
+            architecture: "x86_64".to_string(),
+            packages: vec![openssl, package("libstdc++6", "12.2.0-14", Some("Apache-2.0"))],
+            statistics: InventoryStatistics {
+                total_packages: 2,
+                total_size: 0,
+                vulnerabilities: Default::default(),
+                licenses: Default::default(),
+            },
+            containers: vec![ContainerImageInfo {
+                runtime: "docker".to_string(),
+                id: "sha256:0123456789abcdef".to_string(),
+                tags: vec!["nginx:1.25".to_string()],
+                digests: Vec::new(),
+                containers: Vec::new(),
+                packages: vec![package("nginx", "1.25.3-1", None)],
+            }],
+        }
+    }
+
+    #[test]
+    fn test_cyclonedx() {
+        let bom = to_cyclonedx(&inventory()).unwrap();
+        let json = serde_json::to_value(&bom).unwrap();
+        assert_eq!(json["specVersion"], "1.6");
+        assert_eq!(json["$schema"], "http://cyclonedx.org/schema/bom-1.6.schema.json");
+        assert_eq!(json["metadata"]["tools"]["components"][0]["name"], "guestkit");
+        assert_eq!(
+            json["components"][0]["purl"],
+            "pkg:deb/debian/openssl@3.0.11-1~deb12u2"
+        );
+        assert_eq!(
+            json["components"][1]["purl"],
+            "pkg:deb/debian/libstdc%2B%2B6@12.2.0-14"
+        );
+        assert_eq!(
+            json["vulnerabilities"][0]["affects"][0]["ref"],
+            json["components"][0]["bom-ref"]
+        );
+    }
+
+    #[test]
+    fn test_cyclonedx_xml() {
+        let xml = cyclonedx_xml(&to_cyclonedx(&inventory()).unwrap());
+        let doc = roxmltree::Document::parse(&xml).unwrap();
+        let bom = doc.root_element();
+        assert_eq!(bom.tag_name().namespace(), Some("http://cyclonedx.org/schema/bom/1.6"));
+
+        let components: Vec<_> = bom
+            .descendants()
+            .filter(|n| n.has_tag_name("component") && n.attribute("bom-ref").is_some())
+            .collect();
+        assert_eq!(components.len(), 4);
+        let names: Vec<_> = components[0]
+            .children()
+            .filter(|n| n.is_element())
+            .map(|n| n.tag_name().name())
+            .collect();
+        assert_eq!(names, ["name", "version", "licenses", "purl"]);
+        // The container, with its package nested
+        assert_eq!(components[2].attribute("type"), Some("container"));
+        assert!(components[3].ancestors().any(|n| n == components[2]));
+
+        let severity = bom
+            .descendants()
+            .find(|n| n.has_tag_name("severity"))
+            .and_then(|n| n.text());
+        assert_eq!(severity, Some("medium"));
+    }
+
+    #[test]
+    fn test_spdx3() {
+        let doc = to_spdx3(&inventory()).unwrap();
+        let json = serde_json::to_value(&doc).unwrap();
+        assert_eq!(json["@context"], "https://spdx.org/rdf/3.0.1/spdx-context.jsonld");
+
+        let graph = json["@graph"].as_array().unwrap();
+        let of_type = |kind: &str| -> Vec<&serde_json::Value> {
+            graph.iter().filter(|e| e["type"] == kind).collect()
+        };
+        assert_eq!(of_type("CreationInfo")[0]["specVersion"], "3.0.1");
+        let packages = of_type("software_Package");
+        assert_eq!(packages.len(), 4);
+        assert_eq!(packages[0]["software_packageUrl"], "pkg:deb/debian/openssl@3.0.11-1~deb12u2");
+        assert_eq!(packages[2]["software_primaryPurpose"], "container");
+
+        // One license element shared by both packages
+        assert_eq!(of_type("simplelicensing_LicenseExpression").len(), 1);
+        let vulnerability = of_type("security_Vulnerability")[0];
+        assert_eq!(vulnerability["externalIdentifier"][0]["identifier"], "CVE-2024-0727");
+
+        let relationships = of_type("Relationship");
+        let types: Vec<_> = relationships
+            .iter()
+            .map(|r| r["relationshipType"].as_str().unwrap())
+            .collect();
+        assert_eq!(
+            types,
+            ["hasConcludedLicense", "hasAssociatedVulnerability", "hasConcludedLicense", "contains"]
+        );
+        assert_eq!(relationships[1]["from"], packages[0]["spdxId"]);
+        assert_eq!(relationships[3]["to"][0], packages[3]["spdxId"]);
+
+        let sbom = of_type("software_Sbom")[0];
+        assert_eq!(sbom["rootElement"].as_array().unwrap().len(), 3);
+        assert_eq!(of_type("SpdxDocument")[0]["rootElement"][0], sbom["spdxId"]);
+        assert_eq!(
+            of_type("SpdxDocument")[0]["profileConformance"],
+            serde_json::json!(["core", "software", "simpleLicensing", "security"])
+        );
+    }
+}
@@ -5,9 +5,15 @@ pub mod sbom;
 pub mod formats;
 pub mod cve;
 pub mod licenses;
+pub mod attest;
 
+use crate::cli::plain;
+use crate::cli::target;
+use crate::cli::vulndb::vex::{self, Vex};
+use crate::cli::vulndb::VulnDb;
 use anyhow::{Context, Result};
 use chrono::Utc;
+use cve::CveLookup;
 use guestkit::Guestfs;
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -17,8 +23,14 @@ use std::path::Path;
 /// SBOM output format
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum SbomFormat {
+    /// SPDX 2.3 JSON
     Spdx,
+    /// SPDX 3.0 JSON-LD
+    Spdx3,
+    /// CycloneDX 1.6 JSON
     CycloneDx,
+    /// CycloneDX 1.6 XML
+    CycloneDxXml,
     Json,
     Csv,
 }
@@ -27,7 +39,9 @@ impl SbomFormat {
     pub fn from_str(s: &str) -> Result<Self> {
         match s.to_lowercase().as_str() {
             "spdx" => Ok(Self::Spdx),
+            "spdx3" => Ok(Self::Spdx3),
             "cyclonedx" => Ok(Self::CycloneDx),
+            "cyclonedx-xml" => Ok(Self::CycloneDxXml),
             "json" => Ok(Self::Json),
             "csv" => Ok(Self::Csv),
             _ => anyhow::bail!("Unknown format: {}", s),
@@ -64,12 +78,47 @@ pub struct VulnerabilityInfo {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Inventory {
     pub image_path: String,
+    #[serde(default)]
+    pub root: String,
     pub scanned_at: String,
     pub os_name: String,
     pub os_version: String,
+    /// Distro as `inspect` names it, the namespace of package URLs
+    #[serde(default)]
+    pub distro: String,
     pub architecture: String,
     pub packages: Vec<PackageInfo>,
     pub statistics: InventoryStatistics,
+    /// Container images stored in the guest
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub containers: Vec<ContainerImageInfo>,
+}
+
+/// A container image stored in the guest
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ContainerImageInfo {
+    pub runtime: String,
+    pub id: String,
+    pub tags: Vec<String>,
+    pub digests: Vec<String>,
+    /// Names of the containers created from it
+    pub containers: Vec<String>,
+    /// Only with `--container-sboms`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub packages: Vec<PackageInfo>,
+}
+
+impl ContainerImageInfo {
+    /// The first tag, or the short image ID
+    pub fn name(&self) -> &str {
+        match self.tags.first() {
+            Some(tag) => tag,
+            None => {
+                let hex = self.id.trim_start_matches("sha256:");
+                &hex[..hex.len().min(12)]
+            }
+        }
+    }
 }
 
 /// Inventory statistics
@@ -82,61 +131,76 @@ pub struct InventoryStatistics {
 }
 
 /// Generate inventory from disk image
+///
+/// One inventory per operating system, or only for the one chosen with
+/// `--os-root`. Container images in the guest are listed too, with their
+/// packages if `container_sboms` is set. CVEs come from the database
+/// `guestctl vulndb update` builds, and only for the guests' own packages.
 pub fn generate_inventory<P: AsRef<Path>>(
     image_path: P,
     include_licenses: bool,
     include_cves: bool,
     include_files: bool,
-) -> Result<Inventory> {
+    container_sboms: bool,
+) -> Result<Vec<Inventory>> {
     let image_path_str = image_path.as_ref().display().to_string();
+    let db = if include_cves { Some(VulnDb::open()?) } else { None };
 
     // Initialize guestfs
     let mut g = Guestfs::new()?;
     g.add_drive_opts(&image_path, true, None)?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
     g.launch()?;
 
     // Inspect OS
-    let roots = g.inspect_os()?;
+    let roots = g.inspect_os_selected()?;
     if roots.is_empty() {
         anyhow::bail!("No operating systems found in disk image");
     }
 
-    let root = &roots[0];
-
-    // Mount filesystems
-    let mountpoints = g.inspect_get_mountpoints(root)?;
-    for (mp, dev) in mountpoints {
-        let _ = g.mount(&dev, &mp);
+    let mut inventories = Vec::new();
+    for root in &roots {
+        // Mount filesystems
+        g.umount_all()?;
+        g.mount_os_ro(root)?;
+
+        // Get OS information
+        let os_name = g.inspect_get_product_name(root)
+            .unwrap_or_else(|_| "Unknown".to_string());
+        let os_version = g.inspect_get_product_variant(root)
+            .unwrap_or_else(|_| "Unknown".to_string());
+        let architecture = g.inspect_get_arch(root)
+            .unwrap_or_else(|_| "Unknown".to_string());
+        let distro = g.inspect_get_distro(root).unwrap_or_default();
+
+        // Scan packages
+        let cves = db.as_ref().and_then(|db| CveLookup::for_os(db, &mut g, root));
+        let packages = scan_packages(&mut g, root, include_licenses, cves.as_ref(), include_files)?;
+
+        // Calculate statistics
+        let statistics = calculate_statistics(&packages);
+
+        let containers = scan_containers(&mut g, container_sboms, include_licenses)?;
+
+        inventories.push(Inventory {
+            image_path: image_path_str.clone(),
+            root: root.clone(),
+            scanned_at: Utc::now().to_rfc3339(),
+            os_name,
+            os_version,
+            distro,
+            architecture,
+            packages,
+            statistics,
+            containers,
+        });
     }
 
-    // Get OS information
-    let os_name = g.inspect_get_product_name(root)
-        .unwrap_or_else(|_| "Unknown".to_string());
-    let os_version = g.inspect_get_product_variant(root)
-        .unwrap_or_else(|_| "Unknown".to_string());
-    let architecture = g.inspect_get_arch(root)
-        .unwrap_or_else(|_| "Unknown".to_string());
-
-    // Scan packages
-    let packages = scan_packages(&mut g, root, include_licenses, include_cves, include_files)?;
-
-    // Calculate statistics
-    let statistics = calculate_statistics(&packages);
-
-    let inventory = Inventory {
-        image_path: image_path_str,
-        scanned_at: Utc::now().to_rfc3339(),
-        os_name,
-        os_version,
-        architecture,
-        packages,
-        statistics,
-    };
-
     // Shutdown guestfs
     g.shutdown()?;
 
-    Ok(inventory)
+    Ok(inventories)
 }
 
 /// Scan packages from the guest OS
@@ -144,34 +208,25 @@ fn scan_packages(
     g: &mut Guestfs,
     root: &str,
     include_licenses: bool,
-    include_cves: bool,
-    include_files: bool,
+    cves: Option<&CveLookup>,
+    _include_files: bool,
 ) -> Result<Vec<PackageInfo>> {
     let package_format = g.inspect_get_package_format(root)?;
 
-    match package_format.as_str() {
-        "deb" => scan_deb_packages(g, root, include_licenses, include_cves, include_files),
-        "rpm" => scan_rpm_packages(g, root, include_licenses, include_cves, include_files),
+    // Named by their package URL types
+    let package_type = match package_format.as_str() {
+        "deb" | "rpm" | "apk" | "ebuild" => package_format.as_str(),
+        "pacman" => "alpm",
         _ => anyhow::bail!("Unsupported package format: {}", package_format),
-    }
-}
-
-/// Scan Debian/Ubuntu packages
-fn scan_deb_packages(
-    g: &mut Guestfs,
-    root: &str,
-    include_licenses: bool,
-    include_cves: bool,
-    _include_files: bool,
-) -> Result<Vec<PackageInfo>> {
-    let applications = g.inspect_list_applications2(root)?;
+    };
+    let applications = g.inspect_list_applications(root)?;
     let mut packages = Vec::new();
 
-    for (name, version, _release) in applications {
+    for app in applications {
         let mut pkg = PackageInfo {
-            name: name.clone(),
-            version: version.clone(),
-            package_type: "deb".to_string(),
+            name: app.name.clone(),
+            version: app.full_version(),
+            package_type: package_type.to_string(),
             license: None,
             size: None,
             installed_date: None,
@@ -183,12 +238,12 @@ fn scan_deb_packages(
 
         // Add license information if requested
         if include_licenses {
-            pkg.license = licenses::detect_license(&name, "deb");
+            pkg.license = licenses::detect_license(&app.name, package_type);
         }
 
         // Add CVE information if requested
-        if include_cves {
-            pkg.vulnerabilities = cve::lookup_cves(&name, &version)?;
+        if let Some(cves) = cves {
+            pkg.vulnerabilities = cves.lookup(&app);
         }
 
         packages.push(pkg);
@@ -197,45 +252,96 @@ fn scan_deb_packages(
     Ok(packages)
 }
 
-/// Scan RPM-based packages
-fn scan_rpm_packages(
+/// List the container images in the guest, and read their packages if
+/// `include_packages` is set
+///
+/// Their packages are not looked up for CVEs: the distro release of an
+/// image is not known.
+fn scan_containers(
     g: &mut Guestfs,
-    root: &str,
+    include_packages: bool,
     include_licenses: bool,
-    include_cves: bool,
-    _include_files: bool,
-) -> Result<Vec<PackageInfo>> {
-    let applications = g.inspect_list_applications2(root)?;
-    let mut packages = Vec::new();
-
-    for (name, version, _release) in applications {
-        let mut pkg = PackageInfo {
-            name: name.clone(),
-            version: version.clone(),
-            package_type: "rpm".to_string(),
-            license: None,
-            size: None,
-            installed_date: None,
-            files: Vec::new(),
-            dependencies: Vec::new(),
-            vulnerabilities: Vec::new(),
-            checksum: None,
+) -> Result<Vec<ContainerImageInfo>> {
+    // Unreadable container storage should not cost the host's SBOM
+    let found = match g.inspect_containers() {
+        Ok(found) => found,
+        Err(e) => {
+            log::warn!("Skipping container images: {}", e);
+            return Ok(Vec::new());
+        }
+    };
+    let mut images = Vec::new();
+
+    for image in &found.images {
+        let mut info = ContainerImageInfo {
+            runtime: image.runtime.clone(),
+            id: image.id.clone(),
+            tags: image.tags.clone(),
+            digests: image.digests.clone(),
+            containers: found.containers_of(image).map(|c| c.name.clone()).collect(),
+            packages: Vec::new(),
         };
 
-        // Add license information if requested
-        if include_licenses {
-            pkg.license = licenses::detect_license(&name, "rpm");
+        if include_packages {
+            for (format, app) in g.container_image_packages(image)? {
+                // Named by their package URL types
+                let package_type = match format.as_str() {
+                    "pacman" => "alpm",
+                    other => other,
+                };
+                let mut pkg = PackageInfo {
+                    name: app.name.clone(),
+                    version: app.full_version(),
+                    package_type: package_type.to_string(),
+                    license: None,
+                    size: None,
+                    installed_date: None,
+                    files: Vec::new(),
+                    dependencies: Vec::new(),
+                    vulnerabilities: Vec::new(),
+                    checksum: None,
+                };
+
+                if include_licenses {
+                    pkg.license = licenses::detect_license(&app.name, package_type);
+                }
+
+                info.packages.push(pkg);
+            }
         }
 
-        // Add CVE information if requested
-        if include_cves {
-            pkg.vulnerabilities = cve::lookup_cves(&name, &version)?;
-        }
+        images.push(info);
+    }
 
-        packages.push(pkg);
+    Ok(images)
+}
+
+/// Drop the CVEs a VEX document states the packages are not affected by,
+/// returning how many were dropped
+pub fn apply_vex(inventory: &mut Inventory, vex: &Vex) -> usize {
+    let mut suppressed = 0;
+    for pkg in &mut inventory.packages {
+        let purl = vex::package_purl(
+            &pkg.package_type,
+            &inventory.distro,
+            &pkg.name,
+            &pkg.version,
+            "",
+        );
+        let before = pkg.vulnerabilities.len();
+        pkg.vulnerabilities.retain(|v| !vex.suppresses(&v.cve, &purl));
+        suppressed += before - pkg.vulnerabilities.len();
     }
+    inventory.statistics = calculate_statistics(&inventory.packages);
+    suppressed
+}
 
-    Ok(packages)
+/// Drop the CVEs less severe than `min_severity` (critical, high, medium, low)
+pub fn retain_severity(inventory: &mut Inventory, min_severity: &str) {
+    for pkg in &mut inventory.packages {
+        pkg.vulnerabilities = cve::filter_by_severity(&pkg.vulnerabilities, min_severity);
+    }
+    inventory.statistics = calculate_statistics(&inventory.packages);
 }
 
 /// Calculate inventory statistics
@@ -266,21 +372,29 @@ fn calculate_statistics(packages: &[PackageInfo]) -> InventoryStatistics {
     }
 }
 
-/// Export inventory to specified format
+/// Export inventory to specified format, returning what was written
 pub fn export_inventory(
     inventory: &Inventory,
     format: SbomFormat,
     output: Option<&str>,
-) -> Result<()> {
+) -> Result<String> {
     let content = match format {
         SbomFormat::Spdx => {
             let doc = formats::to_spdx(inventory)?;
             serde_json::to_string_pretty(&doc)?
         }
+        SbomFormat::Spdx3 => {
+            let doc = formats::to_spdx3(inventory)?;
+            serde_json::to_string_pretty(&doc)?
+        }
         SbomFormat::CycloneDx => {
             let bom = formats::to_cyclonedx(inventory)?;
             serde_json::to_string_pretty(&bom)?
         }
+        SbomFormat::CycloneDxXml => {
+            let bom = formats::to_cyclonedx(inventory)?;
+            formats::cyclonedx_xml(&bom)
+        }
         SbomFormat::Json => {
             serde_json::to_string_pretty(inventory)?
         }
@@ -290,12 +404,12 @@ pub fn export_inventory(
     };
 
     if let Some(path) = output {
-        std::fs::write(path, content)
+        std::fs::write(path, &content)
             .context(format!("Failed to write to {}", path))?;
-        println!("✅ SBOM written to: {}", path);
+        println!("{} SBOM written to: {}", plain::text("✅"), path);
     } else {
         println!("{}", content);
     }
 
-    Ok(())
+    Ok(content)
 }
@@ -19,6 +19,19 @@ pub fn generate_summary(inventory: &Inventory) -> String {
     summary.push_str(&format!("Total Packages: {}\n", inventory.statistics.total_packages));
     summary.push_str(&format!("Total Size: {}\n\n", format_size(inventory.statistics.total_size)));
 
+    if !inventory.containers.is_empty() {
+        summary.push_str(&format!("🐳 Container Images: {}\n", inventory.containers.len()));
+        summary.push_str("----------------------\n");
+        for image in &inventory.containers {
+            summary.push_str(&format!("{} ({})", image.name(), image.runtime));
+            if !image.packages.is_empty() {
+                summary.push_str(&format!(": {} packages", image.packages.len()));
+            }
+            summary.push('\n');
+        }
+        summary.push('\n');
+    }
+
     if !inventory.statistics.vulnerabilities.is_empty() {
         summary.push_str(&format!("⚠️  Vulnerabilities\n"));
         summary.push_str(&format!("------------------\n"));
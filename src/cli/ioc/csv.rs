@@ -0,0 +1,141 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! CSV indicator lists
+//!
+//! The first row names the columns. The value column is `value`,
+//! `indicator` or `ioc`; `type`, `severity` (or `threat_level`) and
+//! `description` are optional. Without a type the kind is guessed from the
+//! value. Lines starting with `#` are comments, and a file with a single
+//! unnamed column is read as one value per line.
+
+use super::{Indicator, IocSet, Kind};
+use anyhow::Result;
+
+/// The kind a `type` column names
+fn kind_of(name: &str, value: &str) -> Option<Kind> {
+    match name
+        .trim()
+        .to_ascii_lowercase()
+        .replace(['_', ' '], "-")
+        .as_str()
+    {
+        "md5" => Some(Kind::Md5),
+        "sha1" | "sha-1" => Some(Kind::Sha1),
+        "sha256" | "sha-256" => Some(Kind::Sha256),
+        "hash" | "filehash" | "file-hash" => Kind::hash_of_len(value.trim().len()),
+        "ip" | "ipv4" | "ipv6" | "ip-addr" | "ip-address" => Some(Kind::Ip),
+        "domain" | "hostname" | "host" | "fqdn" | "domain-name" => Some(Kind::Domain),
+        "url" | "uri" => Some(Kind::Url),
+        "path" | "file" | "filepath" | "file-path" => Some(Kind::Path),
+        "filename" | "file-name" | "name" => Some(Kind::FileName),
+        "user" | "username" | "account" => Some(Kind::User),
+        "" => Kind::infer(value),
+        _ => None,
+    }
+}
+
+pub fn parse(content: &str, source: &str) -> Result<IocSet> {
+    let mut reader = ::csv::ReaderBuilder::new()
+        .comment(Some(b'#'))
+        .flexible(true)
+        .trim(::csv::Trim::All)
+        .has_headers(false)
+        .from_reader(content.as_bytes());
+
+    let mut records = reader.records();
+    let Some(header) = records.next().transpose()? else {
+        return Ok(IocSet::default());
+    };
+    let column = |names: &[&str]| {
+        header
+            .iter()
+            .position(|h| names.iter().any(|n| h.eq_ignore_ascii_case(n)))
+    };
+    let value_col = column(&["value", "indicator", "ioc"]);
+    let type_col = column(&["type", "indicator_type", "ioc_type", "kind"]);
+    let severity_col = column(&["severity", "threat_level", "level"]);
+    let description_col = column(&["description", "desc", "comment", "threat"]);
+
+    let mut set = IocSet::default();
+    let mut add = |record: &::csv::StringRecord, line: u64| {
+        let field = |col: Option<usize>| col.and_then(|c| record.get(c)).unwrap_or("");
+        let value = field(value_col.or(Some(0)));
+        let indicator = kind_of(field(type_col), value).and_then(|kind| {
+            Indicator::new(
+                kind,
+                value,
+                field(severity_col),
+                field(description_col),
+                &format!("{}:{}", source, line),
+            )
+        });
+        match indicator {
+            Some(indicator) => set.push(indicator),
+            None => set.skipped += 1,
+        }
+    };
+
+    // A headerless list of values
+    if value_col.is_none() && header.len() == 1 {
+        add(&header, 1);
+    }
+    for record in records {
+        let record = record?;
+        let line = record.position().map_or(0, |p| p.line());
+        add(&record, line);
+    }
+    Ok(set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_csv() {
+        let content = "\
+# exported 2024-05-01
+type,value,severity,description
+sha256,E3B0C44298FC1C149AFBF4C8996FB92427AE41E4649B934CA495991B7852B855,critical,Miner
+ip,203.0.113.7,high,\"C2, stage 2\"
+domain,evil.example,,
+registry,HKLM\\Software\\Evil,high,
+,/dev/shm/.x,low,Dropper path
+";
+        let set = parse(content, "feed.csv").unwrap();
+        let found: Vec<_> = set
+            .indicators
+            .iter()
+            .map(|i| {
+                (
+                    i.kind,
+                    i.value.as_str(),
+                    i.severity.as_str(),
+                    i.description.as_str(),
+                )
+            })
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    Kind::Sha256,
+                    "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+                    "CRITICAL",
+                    "Miner"
+                ),
+                (Kind::Ip, "203.0.113.7", "HIGH", "C2, stage 2"),
+                (Kind::Domain, "evil.example", "HIGH", ""),
+                (Kind::Path, "/dev/shm/.x", "LOW", "Dropper path"),
+            ]
+        );
+        assert_eq!(set.skipped, 1);
+        assert_eq!(set.indicators[1].source, "feed.csv:4");
+    }
+
+    #[test]
+    fn test_parse_value_list() {
+        let set = parse("evil.example\n203.0.113.7\nnot an ioc\n", "list.txt").unwrap();
+        assert_eq!(set.len(), 2);
+        assert_eq!(set.skipped, 1);
+    }
+}
@@ -0,0 +1,305 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Indicators of compromise from threat intelligence feeds
+//!
+//! `intelligence --ioc-file` reads STIX 2.1 bundles, OpenIOC documents and
+//! CSV lists into one [`IocSet`], which [`scan`] matches against a guest.
+//! The format is chosen by extension (`.json`, `.ioc`/`.xml`, `.csv`) and
+//! otherwise sniffed from the content.
+
+pub mod csv;
+pub mod openioc;
+pub mod scan;
+pub mod stix;
+
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::net::IpAddr;
+use std::path::Path;
+
+/// What an indicator's value is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Kind {
+    Md5,
+    Sha1,
+    Sha256,
+    /// Absolute guest path
+    Path,
+    /// File name anywhere in the guest
+    FileName,
+    Domain,
+    Ip,
+    Url,
+    /// Account name
+    User,
+}
+
+impl Kind {
+    /// Indicator type as `intelligence` reports it, e.g. `HASH`
+    pub fn label(self) -> &'static str {
+        match self {
+            Kind::Md5 | Kind::Sha1 | Kind::Sha256 => "HASH",
+            Kind::Path | Kind::FileName => "FILE",
+            Kind::Domain => "DOMAIN",
+            Kind::Ip => "IP",
+            Kind::Url => "URL",
+            Kind::User => "USER",
+        }
+    }
+
+    /// A hash kind by hex digest length
+    pub fn hash_of_len(len: usize) -> Option<Kind> {
+        match len {
+            32 => Some(Kind::Md5),
+            40 => Some(Kind::Sha1),
+            64 => Some(Kind::Sha256),
+            _ => None,
+        }
+    }
+
+    /// Guess the kind of a bare value
+    pub fn infer(value: &str) -> Option<Kind> {
+        let value = value.trim();
+        if value.is_empty() {
+            None
+        } else if value.chars().all(|c| c.is_ascii_hexdigit()) {
+            Kind::hash_of_len(value.len())
+        } else if value.parse::<IpAddr>().is_ok() {
+            Some(Kind::Ip)
+        } else if value.contains("://") {
+            Some(Kind::Url)
+        } else if value.starts_with('/') {
+            Some(Kind::Path)
+        } else if value.contains('.') && !value.contains(char::is_whitespace) {
+            Some(Kind::Domain)
+        } else {
+            None
+        }
+    }
+}
+
+/// One indicator of compromise
+#[derive(Debug, Clone, Serialize)]
+pub struct Indicator {
+    pub kind: Kind,
+    /// Normalized: lowercase hashes and domains, canonical IPs
+    pub value: String,
+    /// CRITICAL, HIGH, MEDIUM or LOW
+    pub severity: String,
+    pub description: String,
+    /// Feed file and, where the feed has one, the indicator's ID
+    pub source: String,
+}
+
+impl Indicator {
+    /// An indicator with its value normalized, or `None` if the value is
+    /// not valid for the kind
+    pub fn new(
+        kind: Kind,
+        value: &str,
+        severity: &str,
+        description: &str,
+        source: &str,
+    ) -> Option<Self> {
+        let value = value.trim();
+        let value = match kind {
+            Kind::Md5 | Kind::Sha1 | Kind::Sha256 => {
+                let hex = value.to_ascii_lowercase();
+                (Kind::hash_of_len(hex.len()) == Some(kind)
+                    && hex.chars().all(|c| c.is_ascii_hexdigit()))
+                .then_some(hex)?
+            }
+            Kind::Domain => value.trim_end_matches('.').to_ascii_lowercase(),
+            Kind::Ip => value.parse::<IpAddr>().ok()?.to_string(),
+            Kind::Path => value.starts_with('/').then(|| value.to_string())?,
+            Kind::FileName => value.rsplit(['/', '\\']).next()?.to_string(),
+            Kind::Url | Kind::User => value.to_string(),
+        };
+        if value.is_empty() {
+            return None;
+        }
+        Some(Self {
+            kind,
+            value,
+            severity: severity_level(severity).unwrap_or("HIGH").to_string(),
+            description: description.trim().to_string(),
+            source: source.to_string(),
+        })
+    }
+}
+
+/// CRITICAL/HIGH/MEDIUM/LOW for a severity name, case-insensitively
+pub fn severity_level(name: &str) -> Option<&'static str> {
+    match name.trim().to_ascii_uppercase().as_str() {
+        "CRITICAL" => Some("CRITICAL"),
+        "HIGH" => Some("HIGH"),
+        "MEDIUM" | "MODERATE" => Some("MEDIUM"),
+        "LOW" | "INFO" | "INFORMATIONAL" => Some("LOW"),
+        _ => None,
+    }
+}
+
+/// Rank of a severity, most severe first
+pub fn severity_rank(severity: &str) -> usize {
+    ["CRITICAL", "HIGH", "MEDIUM", "LOW"]
+        .iter()
+        .position(|s| s.eq_ignore_ascii_case(severity))
+        .unwrap_or(4)
+}
+
+/// Feed formats
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Format {
+    Stix,
+    OpenIoc,
+    Csv,
+}
+
+impl Format {
+    /// The format of a feed file, by extension or content
+    pub fn detect(path: &Path, content: &str) -> Format {
+        match path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.to_ascii_lowercase())
+            .as_deref()
+        {
+            Some("json") | Some("stix") => Format::Stix,
+            Some("ioc") | Some("xml") => Format::OpenIoc,
+            Some("csv") | Some("tsv") | Some("txt") => Format::Csv,
+            _ => match content.trim_start().chars().next() {
+                Some('{') => Format::Stix,
+                Some('<') => Format::OpenIoc,
+                _ => Format::Csv,
+            },
+        }
+    }
+}
+
+/// Indicators from any number of feeds
+#[derive(Debug, Default)]
+pub struct IocSet {
+    pub indicators: Vec<Indicator>,
+    /// Feed entries that could not be turned into indicators, e.g. STIX
+    /// patterns with operators other than `=`
+    pub skipped: usize,
+}
+
+impl IocSet {
+    /// Read a feed file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        let source = path
+            .file_name()
+            .map(|n| n.to_string_lossy().into_owned())
+            .unwrap_or_default();
+        let set = match Format::detect(path, &content) {
+            Format::Stix => stix::parse(&content, &source),
+            Format::OpenIoc => openioc::parse(&content, &source),
+            Format::Csv => csv::parse(&content, &source),
+        };
+        set.with_context(|| format!("Invalid IOC file {}", path.display()))
+    }
+
+    /// Add another feed's indicators, dropping repeats of the same value
+    pub fn extend(&mut self, other: IocSet) {
+        for indicator in other.indicators {
+            self.push(indicator);
+        }
+        self.skipped += other.skipped;
+    }
+
+    /// Add an indicator unless one with the same value is present; the
+    /// more severe of the two is kept
+    pub fn push(&mut self, indicator: Indicator) {
+        match self
+            .indicators
+            .iter_mut()
+            .find(|i| i.kind == indicator.kind && i.value == indicator.value)
+        {
+            Some(existing)
+                if severity_rank(&indicator.severity) < severity_rank(&existing.severity) =>
+            {
+                *existing = indicator;
+            }
+            Some(_) => {}
+            None => self.indicators.push(indicator),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.indicators.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.indicators.is_empty()
+    }
+
+    /// Number of indicators of each type label
+    pub fn counts(&self) -> Vec<(&'static str, usize)> {
+        let mut counts: Vec<(&'static str, usize)> = Vec::new();
+        for indicator in &self.indicators {
+            let label = indicator.kind.label();
+            match counts.iter_mut().find(|(l, _)| *l == label) {
+                Some((_, n)) => *n += 1,
+                None => counts.push((label, 1)),
+            }
+        }
+        counts.sort();
+        counts
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_infer_and_normalize() {
+        assert_eq!(
+            Kind::infer("44D88612FEA8A8F36DE82E1278ABB02F"),
+            Some(Kind::Md5)
+        );
+        assert_eq!(Kind::infer("2001:db8::1"), Some(Kind::Ip));
+        assert_eq!(Kind::infer("http://evil.example/x"), Some(Kind::Url));
+        assert_eq!(Kind::infer("/tmp/.x/kworker"), Some(Kind::Path));
+        assert_eq!(Kind::infer("Evil.Example."), Some(Kind::Domain));
+        assert_eq!(Kind::infer("backdoor"), None);
+
+        let i =
+            Indicator::new(Kind::Domain, "Evil.Example.", "critical", "C2", "feed.csv").unwrap();
+        assert_eq!(
+            (i.value.as_str(), i.severity.as_str()),
+            ("evil.example", "CRITICAL")
+        );
+        let i = Indicator::new(Kind::Ip, "2001:0db8::0001", "", "", "").unwrap();
+        assert_eq!(i.value, "2001:db8::1");
+        assert!(
+            Indicator::new(Kind::Sha1, "44d88612fea8a8f36de82e1278abb02f", "", "", "").is_none()
+        );
+        assert!(Indicator::new(Kind::Path, "relative/path", "", "", "").is_none());
+    }
+
+    #[test]
+    fn test_push_keeps_most_severe() {
+        let mut set = IocSet::default();
+        set.push(Indicator::new(Kind::Ip, "203.0.113.7", "medium", "scanner", "a").unwrap());
+        set.push(Indicator::new(Kind::Ip, "203.0.113.7", "critical", "C2", "b").unwrap());
+        set.push(Indicator::new(Kind::Ip, "203.0.113.7", "low", "", "c").unwrap());
+        assert_eq!(set.len(), 1);
+        assert_eq!(set.indicators[0].description, "C2");
+        assert_eq!(set.counts(), [("IP", 1)]);
+    }
+
+    #[test]
+    fn test_detect_format() {
+        assert_eq!(Format::detect(Path::new("feed.json"), ""), Format::Stix);
+        assert_eq!(Format::detect(Path::new("apt.ioc"), ""), Format::OpenIoc);
+        assert_eq!(
+            Format::detect(Path::new("feed"), "  <?xml"),
+            Format::OpenIoc
+        );
+        assert_eq!(Format::detect(Path::new("feed"), "type,value"), Format::Csv);
+    }
+}
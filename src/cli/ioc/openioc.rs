@@ -0,0 +1,159 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! OpenIOC 1.0 and 1.1 documents
+//!
+//! Every `IndicatorItem` with an `is` condition on a search term below
+//! becomes an indicator; the logic joining the items is not evaluated.
+//! Negated items are left out.
+
+use super::{Indicator, IocSet, Kind};
+use anyhow::{bail, Result};
+
+/// Search terms guestctl can match, by OpenIOC `Context/@search`
+const SEARCH_TERMS: &[(&str, Kind)] = &[
+    ("FileItem/Md5sum", Kind::Md5),
+    ("FileItem/Sha1sum", Kind::Sha1),
+    ("FileItem/Sha256sum", Kind::Sha256),
+    ("FileItem/FullPath", Kind::Path),
+    ("FileItem/FileName", Kind::FileName),
+    ("ProcessItem/path", Kind::Path),
+    ("ProcessItem/name", Kind::FileName),
+    ("DnsEntryItem/Host", Kind::Domain),
+    ("DnsEntryItem/RecordName", Kind::Domain),
+    ("DnsEntryItem/RecordData/Host", Kind::Domain),
+    ("DnsEntryItem/RecordData/IPv4Address", Kind::Ip),
+    ("Network/DNS", Kind::Domain),
+    ("PortItem/remoteIP", Kind::Ip),
+    ("Network/URI", Kind::Url),
+    ("UrlHistoryItem/URL", Kind::Url),
+    ("UrlHistoryItem/HostName", Kind::Domain),
+    ("UserItem/Username", Kind::User),
+];
+
+pub fn parse(content: &str, source: &str) -> Result<IocSet> {
+    let doc = roxmltree::Document::parse(content)?;
+    let root = doc.root_element();
+    if !matches!(root.tag_name().name(), "ioc" | "OpenIOC") {
+        bail!(
+            "not an OpenIOC document (root element <{}>)",
+            root.tag_name().name()
+        );
+    }
+
+    let text_of = |name: &str| {
+        root.descendants()
+            .find(|n| n.tag_name().name() == name)
+            .and_then(|n| n.text())
+            .map(str::trim)
+            .filter(|t| !t.is_empty())
+    };
+    let description = text_of("short_description")
+        .or_else(|| text_of("description"))
+        .unwrap_or("OpenIOC indicator");
+    let origin = match root.attribute("id") {
+        Some(id) => format!("{} {}", source, id),
+        None => source.to_string(),
+    };
+
+    let mut set = IocSet::default();
+    for item in root
+        .descendants()
+        .filter(|n| n.tag_name().name() == "IndicatorItem")
+    {
+        let condition = item.attribute("condition").unwrap_or("is");
+        let negated = item.attribute("negate") == Some("true");
+        let child = |name: &str| item.children().find(|n| n.tag_name().name() == name);
+        let search = child("Context").and_then(|c| c.attribute("search"));
+        let value = child("Content").and_then(|c| c.text()).unwrap_or("");
+
+        let kind = search.and_then(|search| {
+            SEARCH_TERMS
+                .iter()
+                .find(|(term, _)| term.eq_ignore_ascii_case(search))
+                .map(|(_, kind)| *kind)
+        });
+        let indicator = match kind {
+            Some(kind) if condition == "is" && !negated => {
+                Indicator::new(kind, value, "HIGH", description, &origin)
+            }
+            _ => None,
+        };
+        match indicator {
+            Some(indicator) => set.push(indicator),
+            None => set.skipped += 1,
+        }
+    }
+    Ok(set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_openioc_10() {
+        let xml = r#"<?xml version="1.0" encoding="us-ascii"?>
+<ioc xmlns="http://schemas.mandiant.com/2010/ioc" id="6d2a1b03-b216-4cd8-9a9e-8827af6ebf93">
+  <short_description>Webshell dropper</short_description>
+  <definition>
+    <Indicator operator="OR" id="a1">
+      <IndicatorItem id="b1" condition="is">
+        <Context document="FileItem" search="FileItem/Md5sum" type="mir" />
+        <Content type="md5">44D88612FEA8A8F36DE82E1278ABB02F</Content>
+      </IndicatorItem>
+      <IndicatorItem id="b2" condition="contains">
+        <Context document="FileItem" search="FileItem/FullPath" type="mir" />
+        <Content type="string">/var/www</Content>
+      </IndicatorItem>
+      <Indicator operator="AND" id="a2">
+        <IndicatorItem id="b3" condition="is">
+          <Context document="PortItem" search="PortItem/remoteIP" type="mir" />
+          <Content type="IP">203.0.113.7</Content>
+        </IndicatorItem>
+        <IndicatorItem id="b4" condition="is">
+          <Context document="RegistryItem" search="RegistryItem/Path" type="mir" />
+          <Content type="string">HKLM\Software\Evil</Content>
+        </IndicatorItem>
+      </Indicator>
+    </Indicator>
+  </definition>
+</ioc>"#;
+        let set = parse(xml, "shell.ioc").unwrap();
+        let found: Vec<_> = set
+            .indicators
+            .iter()
+            .map(|i| (i.kind, i.value.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (Kind::Md5, "44d88612fea8a8f36de82e1278abb02f"),
+                (Kind::Ip, "203.0.113.7")
+            ]
+        );
+        assert_eq!(set.indicators[0].description, "Webshell dropper");
+        assert_eq!(set.skipped, 2);
+    }
+
+    #[test]
+    fn test_parse_openioc_11_negate() {
+        let xml = r#"<OpenIOC xmlns="http://openioc.org/schemas/OpenIOC_1.1" id="x">
+  <metadata><short_description>Miner</short_description></metadata>
+  <criteria>
+    <Indicator operator="OR" id="a">
+      <IndicatorItem id="b" condition="is" negate="false">
+        <Context document="DnsEntryItem" search="DnsEntryItem/Host" type="mir" />
+        <Content type="string">Pool.Evil.Example</Content>
+      </IndicatorItem>
+      <IndicatorItem id="c" condition="is" negate="true">
+        <Context document="FileItem" search="FileItem/FileName" type="mir" />
+        <Content type="string">bash</Content>
+      </IndicatorItem>
+    </Indicator>
+  </criteria>
+</OpenIOC>"#;
+        let set = parse(xml, "miner.ioc").unwrap();
+        assert_eq!(set.len(), 1);
+        assert_eq!(set.indicators[0].value, "pool.evil.example");
+        assert!(parse("<stix/>", "x.xml").is_err());
+    }
+}
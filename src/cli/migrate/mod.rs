@@ -5,6 +5,8 @@ pub mod analyzer;
 pub mod planner;
 pub mod reporter;
 
+use crate::cli::plain;
+use crate::cli::target;
 use anyhow::Result;
 use guestkit::Guestfs;
 use serde::{Deserialize, Serialize};
@@ -156,12 +158,14 @@ pub fn analyze_source<P: AsRef<Path>>(image_path: P, verbose: bool) -> Result<So
     let image_path_str = image_path.as_ref().display().to_string();
 
     if verbose {
-        println!("🔍 Analyzing source system: {}", image_path_str);
+        println!("{}Analyzing source system: {}", plain::text("🔍 "), image_path_str);
     }
 
     // Initialize guestfs
     let mut g = Guestfs::new()?;
     g.add_drive_opts(&image_path, true, None)?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
     g.launch()?;
 
     // Inspect OS
@@ -173,10 +177,7 @@ pub fn analyze_source<P: AsRef<Path>>(image_path: P, verbose: bool) -> Result<So
     let root = &roots[0];
 
     // Mount filesystems
-    let mountpoints = g.inspect_get_mountpoints(root)?;
-    for (mp, dev) in mountpoints {
-        let _ = g.mount(&dev, &mp);
-    }
+    g.mount_os_ro(root)?;
 
     // Get OS information
     let os_name = g.inspect_get_product_name(root)?;
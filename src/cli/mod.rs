@@ -2,27 +2,71 @@
 //! CLI module for guestctl
 
 pub mod ai;
+pub mod align;
+pub mod attack;
+pub mod audit_log;
 pub mod batch;
+pub mod bitmap;
 pub mod blueprint;
 pub mod cache;
+pub mod catalog;
+pub mod chain;
+pub mod collect;
 pub mod commands;
+pub mod convert_boot;
 pub mod cost;
+pub mod customize;
+pub mod crash;
 pub mod dependencies;
 pub mod diff;
+pub mod doctor;
 pub mod errors;
 pub mod exporters;
+pub mod fleet;
 pub mod formatters;
+pub mod generate;
+pub mod i18n;
+pub mod import_ova;
 pub mod interactive;
 pub mod inventory;
+pub mod ioc;
+pub mod journal;
+pub mod keys;
 pub mod license;
+pub mod lint;
+pub mod macb;
+pub mod malware;
+pub mod matcher;
 pub mod migrate;
+pub mod notify;
+#[cfg(feature = "oci")]
+pub mod oci;
 pub mod output;
+pub mod package_history;
 pub mod parallel;
+pub mod permissions;
+pub mod plain;
 pub mod plan;
+pub mod plugins;
+pub mod preview;
+pub mod pristine;
 pub mod profiles;
+pub mod provenance;
+#[cfg(feature = "publish")]
+pub mod publish;
+pub mod recover;
+pub mod replay;
+pub mod sarif;
+pub mod secrets;
+pub mod share;
 pub mod shell;
+pub mod support_bundle;
+pub mod sync;
+pub mod target;
 pub mod tui;
 pub mod validate;
+pub mod vulndb;
+pub mod yara;
 
 pub use batch::*;
 pub use interactive::*;
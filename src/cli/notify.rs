@@ -0,0 +1,291 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Notifications when a command finishes
+//!
+//! `--notify` (or `GUESTCTL_NOTIFY`, comma-separated) names where to report
+//! that a command succeeded or failed: `desktop` for a desktop notification
+//! through notify-send, or an http(s) URL a JSON summary is POSTed to.
+//! Failing to notify never changes the command's result.
+
+use crate::cli::plain;
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, Utc};
+use colored::Colorize;
+use serde::Serialize;
+use std::fmt;
+use std::process::Command;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Notification targets used when `--notify` is not given
+pub const NOTIFY_ENV: &str = "GUESTCTL_NOTIFY";
+
+/// Seconds used when `--notify-after` is not given
+pub const NOTIFY_AFTER_ENV: &str = "GUESTCTL_NOTIFY_AFTER";
+
+/// Where a notification goes
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NotifyTarget {
+    /// Desktop notification through notify-send
+    Desktop,
+    /// URL the JSON summary is POSTed to
+    Webhook(String),
+}
+
+impl FromStr for NotifyTarget {
+    type Err = String;
+
+    fn from_str(value: &str) -> Result<Self, Self::Err> {
+        match value {
+            "desktop" => Ok(NotifyTarget::Desktop),
+            url if url.starts_with("http://") || url.starts_with("https://") => {
+                Ok(NotifyTarget::Webhook(url.to_string()))
+            }
+            other => Err(format!(
+                "'{}' is neither 'desktop' nor an http(s) URL",
+                other
+            )),
+        }
+    }
+}
+
+impl fmt::Display for NotifyTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            NotifyTarget::Desktop => write!(f, "desktop"),
+            NotifyTarget::Webhook(url) => write!(f, "{}", url),
+        }
+    }
+}
+
+/// Parse a `--notify` value (clap value parser)
+pub fn parse_notify_target(value: &str) -> Result<NotifyTarget, String> {
+    value.parse()
+}
+
+/// What a notification reports
+#[derive(Debug, Clone, Serialize)]
+pub struct CommandSummary {
+    /// One line for chat webhooks (Slack, Mattermost) and desktop popups
+    pub text: String,
+    /// Subcommand, e.g. "convert"
+    pub command: String,
+    pub arguments: Vec<String>,
+    /// "success" or "failure"
+    pub status: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub host: Option<String>,
+    pub started_at: DateTime<Utc>,
+    pub finished_at: DateTime<Utc>,
+    pub duration_secs: u64,
+}
+
+impl CommandSummary {
+    pub fn succeeded(&self) -> bool {
+        self.error.is_none()
+    }
+}
+
+/// Reports the outcome of a command to its notification targets
+pub struct Notifier {
+    command: String,
+    targets: Vec<NotifyTarget>,
+    /// Commands finishing sooner do not notify
+    min_duration: Duration,
+    started_at: DateTime<Utc>,
+    started: Instant,
+}
+
+impl Notifier {
+    /// Start timing `command`
+    ///
+    /// `targets` and `min_secs` come from the command line; when not given,
+    /// `GUESTCTL_NOTIFY` and `GUESTCTL_NOTIFY_AFTER` are used.
+    pub fn start(command: &str, targets: &[NotifyTarget], min_secs: Option<u64>) -> Result<Self> {
+        let targets = if targets.is_empty() {
+            match std::env::var(NOTIFY_ENV) {
+                Ok(value) => value
+                    .split(',')
+                    .map(str::trim)
+                    .filter(|target| !target.is_empty())
+                    .map(|target| target.parse().map_err(anyhow::Error::msg))
+                    .collect::<Result<_>>()
+                    .with_context(|| format!("Invalid {}", NOTIFY_ENV))?,
+                Err(_) => Vec::new(),
+            }
+        } else {
+            targets.to_vec()
+        };
+        let min_secs = match min_secs {
+            Some(secs) => secs,
+            None => match std::env::var(NOTIFY_AFTER_ENV) {
+                Ok(value) => value
+                    .trim()
+                    .parse()
+                    .with_context(|| format!("Invalid {}", NOTIFY_AFTER_ENV))?,
+                Err(_) => 0,
+            },
+        };
+
+        Ok(Self {
+            command: command.to_string(),
+            targets,
+            min_duration: Duration::from_secs(min_secs),
+            started_at: Utc::now(),
+            started: Instant::now(),
+        })
+    }
+
+    /// Send the outcome of the command to every target
+    ///
+    /// Targets that cannot be reached are reported on stderr.
+    pub fn finish<T>(&self, outcome: &Result<T>) {
+        if self.targets.is_empty() || self.started.elapsed() < self.min_duration {
+            return;
+        }
+
+        let summary = self.summary(outcome.as_ref().err());
+        for target in &self.targets {
+            let sent = match target {
+                NotifyTarget::Desktop => notify_desktop(&summary),
+                NotifyTarget::Webhook(url) => notify_webhook(url, &summary),
+            };
+            if let Err(e) = sent {
+                eprintln!("{} Failed to notify {}: {:#}", plain::text("⚠").yellow(), target, e);
+            }
+        }
+    }
+
+    fn summary(&self, error: Option<&anyhow::Error>) -> CommandSummary {
+        let duration_secs = self.started.elapsed().as_secs();
+        let (status, text) = match error {
+            None => (
+                "success",
+                format!(
+                    "guestctl {} finished in {}",
+                    self.command,
+                    format_duration(duration_secs)
+                ),
+            ),
+            Some(e) => (
+                "failure",
+                format!(
+                    "guestctl {} failed after {}: {:#}",
+                    self.command,
+                    format_duration(duration_secs),
+                    e
+                ),
+            ),
+        };
+
+        CommandSummary {
+            text,
+            command: self.command.clone(),
+            arguments: std::env::args().collect(),
+            status: status.to_string(),
+            error: error.map(|e| format!("{:#}", e)),
+            host: std::fs::read_to_string("/proc/sys/kernel/hostname")
+                .ok()
+                .map(|name| name.trim().to_string())
+                .filter(|name| !name.is_empty()),
+            started_at: self.started_at,
+            finished_at: Utc::now(),
+            duration_secs,
+        }
+    }
+}
+
+/// "1h 02m 03s", "4m 05s" or "6s"
+fn format_duration(secs: u64) -> String {
+    let (hours, minutes, seconds) = (secs / 3600, secs / 60 % 60, secs % 60);
+    if hours > 0 {
+        format!("{}h {:02}m {:02}s", hours, minutes, seconds)
+    } else if minutes > 0 {
+        format!("{}m {:02}s", minutes, seconds)
+    } else {
+        format!("{}s", seconds)
+    }
+}
+
+fn notify_desktop(summary: &CommandSummary) -> Result<()> {
+    let (urgency, title) = if summary.succeeded() {
+        ("normal", format!("guestctl {} finished", summary.command))
+    } else {
+        ("critical", format!("guestctl {} failed", summary.command))
+    };
+    let status = Command::new("notify-send")
+        .arg("--app-name=guestctl")
+        .arg(format!("--urgency={}", urgency))
+        .arg(title)
+        .arg(&summary.text)
+        .status()
+        .context("Failed to execute notify-send. Is libnotify installed?")?;
+    if !status.success() {
+        bail!("notify-send exited with {}", status);
+    }
+    Ok(())
+}
+
+#[cfg(feature = "notify")]
+fn notify_webhook(url: &str, summary: &CommandSummary) -> Result<()> {
+    let response = reqwest::blocking::Client::builder()
+        .user_agent(concat!("guestkit/", env!("CARGO_PKG_VERSION")))
+        .timeout(Duration::from_secs(30))
+        .build()?
+        .post(url)
+        .header(reqwest::header::CONTENT_TYPE, "application/json")
+        .body(serde_json::to_vec(summary)?)
+        .send()?;
+    if !response.status().is_success() {
+        bail!("webhook answered {}", response.status());
+    }
+    Ok(())
+}
+
+#[cfg(not(feature = "notify"))]
+fn notify_webhook(_url: &str, _summary: &CommandSummary) -> Result<()> {
+    bail!("guestctl was built without the notify feature")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_notify_target() {
+        assert_eq!(parse_notify_target("desktop"), Ok(NotifyTarget::Desktop));
+        assert_eq!(
+            parse_notify_target("https://hooks.example.com/T0/B0"),
+            Ok(NotifyTarget::Webhook(
+                "https://hooks.example.com/T0/B0".to_string()
+            ))
+        );
+        assert!(parse_notify_target("mail:ops@example.com").is_err());
+    }
+
+    #[test]
+    fn test_summary() {
+        let notifier = Notifier::start("convert", &[NotifyTarget::Desktop], None).unwrap();
+
+        let summary = notifier.summary(None);
+        assert!(summary.succeeded());
+        assert_eq!(summary.status, "success");
+        assert_eq!(summary.text, "guestctl convert finished in 0s");
+
+        let error = anyhow::anyhow!("qemu-img failed").context("Conversion failed");
+        let summary = notifier.summary(Some(&error));
+        assert_eq!(summary.status, "failure");
+        assert_eq!(
+            summary.error.as_deref(),
+            Some("Conversion failed: qemu-img failed")
+        );
+    }
+
+    #[test]
+    fn test_format_duration() {
+        assert_eq!(format_duration(6), "6s");
+        assert_eq!(format_duration(245), "4m 05s");
+        assert_eq!(format_duration(3723), "1h 02m 03s");
+    }
+}
@@ -0,0 +1,295 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! OCI command - push and pull disk images to and from registries
+
+use super::connect;
+use crate::cli::plain;
+use anyhow::{Context, Result};
+use clap::{Args, Subcommand};
+use colored::*;
+use guestkit::oci::{self, Attachment, Credentials, PushOptions, Reference, RegistryClient};
+use std::collections::BTreeMap;
+use std::io::BufRead;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Args)]
+pub struct OciCommand {
+    /// Registry user name (default: podman/docker login)
+    #[arg(long, global = true)]
+    pub username: Option<String>,
+
+    /// Read the registry password from stdin
+    #[arg(long, global = true, requires = "username")]
+    pub password_stdin: bool,
+
+    /// Talk HTTP instead of HTTPS (local test registries)
+    #[arg(long, global = true)]
+    pub plain_http: bool,
+
+    #[command(subcommand)]
+    pub action: OciAction,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum OciAction {
+    /// Push a disk image to a registry
+    Push {
+        /// Disk image to push
+        image: PathBuf,
+
+        /// Target reference (registry/repository[:tag])
+        #[arg(value_parser = parse_reference)]
+        reference: Reference,
+
+        /// File to attach, e.g. an SBOM or inspection report (repeatable;
+        /// media type inferred from the content unless given)
+        #[arg(short, long = "attach", value_name = "FILE[:MEDIA_TYPE]", value_parser = parse_attachment)]
+        attachments: Vec<Attachment>,
+
+        /// Disk format (default: detected)
+        #[arg(long)]
+        disk_format: Option<String>,
+
+        /// Manifest annotation (repeatable)
+        #[arg(long = "annotation", value_name = "KEY=VALUE", value_parser = parse_annotation)]
+        annotations: Vec<(String, String)>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Pull a disk image and its attachments from a registry
+    Pull {
+        /// Reference (registry/repository[:tag][@digest])
+        #[arg(value_parser = parse_reference)]
+        reference: Reference,
+
+        /// Directory to download into
+        #[arg(short = 'd', long, default_value = ".")]
+        dest: PathBuf,
+
+        /// Only pull the disk image
+        #[arg(long)]
+        no_attachments: bool,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// List the attachments of a disk image
+    Attachments {
+        /// Reference (registry/repository[:tag][@digest])
+        #[arg(value_parser = parse_reference)]
+        reference: Reference,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+}
+
+impl OciCommand {
+    pub fn execute(&self) -> Result<()> {
+        let credentials = self.credentials()?;
+        let reference = match &self.action {
+            OciAction::Push { reference, .. }
+            | OciAction::Pull { reference, .. }
+            | OciAction::Attachments { reference, .. } => reference,
+        };
+        let mut client = connect(reference, credentials, self.plain_http)?;
+
+        match &self.action {
+            OciAction::Push {
+                image,
+                reference,
+                attachments,
+                disk_format,
+                annotations,
+                format,
+            } => {
+                let options = PushOptions {
+                    format: disk_format.clone(),
+                    annotations: annotations.iter().cloned().collect::<BTreeMap<_, _>>(),
+                    attachments: attachments.clone(),
+                };
+                push(&mut client, reference, image, &options, format)
+            }
+            OciAction::Pull {
+                reference,
+                dest,
+                no_attachments,
+                format,
+            } => pull(&mut client, reference, dest, !no_attachments, format),
+            OciAction::Attachments { reference, format } => {
+                list_attachments(&mut client, reference, format)
+            }
+        }
+    }
+
+    fn credentials(&self) -> Result<Option<Credentials>> {
+        let Some(username) = &self.username else {
+            return Ok(None);
+        };
+        let password = if self.password_stdin {
+            let mut password = String::new();
+            std::io::stdin()
+                .lock()
+                .read_line(&mut password)
+                .context("Failed to read password from stdin")?;
+            password.trim_end_matches(['\r', '\n']).to_string()
+        } else {
+            rpassword::prompt_password(format!("Password for {}: ", username))
+                .context("Failed to read password")?
+        };
+        Ok(Some(Credentials::new(username, password)))
+    }
+}
+
+fn push(
+    client: &mut RegistryClient,
+    reference: &Reference,
+    image: &Path,
+    options: &PushOptions,
+    format: &str,
+) -> Result<()> {
+    if !image.is_file() {
+        anyhow::bail!("Image not found: {}", image.display());
+    }
+    if format != "json" {
+        println!(
+            "{} Pushing {} to {}...",
+            plain::text("→").cyan(),
+            image.display(),
+            reference.to_string().bold()
+        );
+    }
+
+    let pushed = oci::push_disk(client, reference, image, options)
+        .with_context(|| format!("Failed to push {}", reference))?;
+
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&pushed)?);
+        return Ok(());
+    }
+
+    println!(
+        "{} Pushed {} ({} bytes)",
+        plain::text("✓").green(),
+        pushed.reference.to_string().bold(),
+        pushed.disk.size
+    );
+    for (attachment, descriptor) in options.attachments.iter().zip(&pushed.attachments) {
+        println!(
+            "  {} {} as {} ({})",
+            "+".green(),
+            attachment.path.display(),
+            attachment.media_type,
+            descriptor.digest.bright_black()
+        );
+    }
+    Ok(())
+}
+
+fn pull(
+    client: &mut RegistryClient,
+    reference: &Reference,
+    dest: &Path,
+    attachments: bool,
+    format: &str,
+) -> Result<()> {
+    if format != "json" {
+        println!("{} Pulling {}...", plain::text("→").cyan(), reference.to_string().bold());
+    }
+
+    let pulled = oci::pull_disk(client, reference, dest, attachments)
+        .with_context(|| format!("Failed to pull {}", reference))?;
+
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&pulled)?);
+        return Ok(());
+    }
+
+    println!(
+        "{} Pulled {} image {} ({})",
+        plain::text("✓").green(),
+        pulled.format,
+        pulled.disk.display().to_string().bold(),
+        pulled.digest.bright_black()
+    );
+    for path in &pulled.attachments {
+        println!("  {} {}", "+".green(), path.display());
+    }
+    Ok(())
+}
+
+fn list_attachments(
+    client: &mut RegistryClient,
+    reference: &Reference,
+    format: &str,
+) -> Result<()> {
+    let (_, digest) = oci::fetch_disk_manifest(client, reference)
+        .with_context(|| format!("Failed to fetch {}", reference))?;
+    let referrers = client.referrers(&reference.repository, &digest)?;
+
+    if format == "json" {
+        println!("{}", serde_json::to_string_pretty(&referrers)?);
+        return Ok(());
+    }
+
+    if referrers.is_empty() {
+        println!("No attachments for {}", reference);
+        return Ok(());
+    }
+
+    println!(
+        "{:<44}  {:<20}  {}",
+        "TYPE".bold(),
+        "CREATED".bold(),
+        "DIGEST".bold()
+    );
+    for referrer in &referrers {
+        println!(
+            "{:<44}  {:<20}  {}",
+            referrer.artifact_type.as_deref().unwrap_or("-"),
+            referrer
+                .annotations
+                .get(oci::ANNOTATION_CREATED)
+                .map(String::as_str)
+                .unwrap_or("-"),
+            referrer.digest.bright_black()
+        );
+    }
+    Ok(())
+}
+
+fn parse_reference(value: &str) -> std::result::Result<Reference, String> {
+    value
+        .strip_prefix(super::OCI_SCHEME)
+        .unwrap_or(value)
+        .parse()
+        .map_err(|e: guestkit::Error| e.to_string())
+}
+
+/// Parse `FILE[:MEDIA_TYPE]`; a suffix is a media type only if it has a `/`
+fn parse_attachment(value: &str) -> std::result::Result<Attachment, String> {
+    let (path, media_type) = match value.rsplit_once(':') {
+        Some((path, media_type)) if media_type.contains('/') => (path, Some(media_type)),
+        _ => (value, None),
+    };
+    if !Path::new(path).is_file() {
+        return Err(format!("Attachment not found: {}", path));
+    }
+
+    match media_type {
+        Some(media_type) => Ok(Attachment::with_media_type(path, media_type)),
+        None => Attachment::new(path).map_err(|e| format!("{}: {}", path, e)),
+    }
+}
+
+fn parse_annotation(value: &str) -> std::result::Result<(String, String), String> {
+    match value.split_once('=') {
+        Some((key, value)) if !key.is_empty() => Ok((key.to_string(), value.to_string())),
+        _ => Err(format!("Expected KEY=VALUE (got {})", value)),
+    }
+}
@@ -0,0 +1,73 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! OCI registry interop: disk images as registry artifacts
+//!
+//! `guestctl oci push` uploads a disk image, with SBOMs and reports attached
+//! as referrers, and `guestctl oci pull` downloads it again. Image arguments
+//! of other commands also accept `oci://registry/repository:tag`, which pulls
+//! the image into the user cache directory first.
+
+pub mod command;
+
+pub use command::OciCommand;
+
+use anyhow::{Context, Result};
+use guestkit::oci::{self, Credentials, Reference, RegistryClient};
+use std::path::PathBuf;
+
+/// Scheme of registry references in image arguments
+pub const OCI_SCHEME: &str = "oci://";
+
+/// Client for the registry of `reference`
+///
+/// Without explicit credentials the podman/docker login files are used.
+/// Registries on the local host are spoken to over plain HTTP.
+pub fn connect(
+    reference: &Reference,
+    credentials: Option<Credentials>,
+    plain_http: bool,
+) -> Result<RegistryClient> {
+    let host = reference.registry.split(':').next().unwrap_or_default();
+    let plain_http = plain_http || matches!(host, "localhost" | "127.0.0.1");
+    let credentials = credentials.or_else(|| Credentials::from_auth_files(&reference.registry));
+
+    Ok(RegistryClient::new(reference.api_host(), plain_http)?.with_credentials(credentials))
+}
+
+/// Pull the disk image at `reference` into the cache and return its path
+///
+/// Images are cached by manifest digest, so a tag is only downloaded again
+/// when it has moved.
+pub fn pull_cached(reference: &str) -> Result<PathBuf> {
+    let reference: Reference = reference.parse()?;
+    let mut client = connect(&reference, None, false)?;
+    let digest = client
+        .resolve_digest(&reference.repository, reference.target())
+        .with_context(|| format!("Failed to resolve {}", reference))?;
+
+    let dir = cache_dir().join(digest.replacen(':', "-", 1));
+    if let Some(disk) = cached_disk(&dir) {
+        return Ok(disk);
+    }
+
+    eprintln!("Pulling {}...", reference);
+    let pulled = oci::pull_disk(&mut client, &reference.with_digest(&digest), &dir, false)
+        .with_context(|| format!("Failed to pull {}", reference))?;
+    Ok(pulled.disk)
+}
+
+/// Directory holding pulled images
+fn cache_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("guestctl")
+        .join("oci")
+}
+
+/// The completely downloaded image in a cache directory
+fn cached_disk(dir: &std::path::Path) -> Option<PathBuf> {
+    std::fs::read_dir(dir)
+        .ok()?
+        .filter_map(|entry| entry.ok())
+        .map(|entry| entry.path())
+        .find(|path| path.is_file() && path.extension().is_none_or(|ext| ext != "part"))
+}
@@ -1,9 +1,11 @@
 // SPDX-License-Identifier: LGPL-3.0-or-later
 //! Output formatting utilities for CLI
 
+use crate::cli::plain;
 use owo_colors::OwoColorize;
 use serde::Serialize;
 use std::fmt;
+use unicode_width::{UnicodeWidthChar, UnicodeWidthStr};
 
 /// Output format options
 #[derive(Debug, Clone, Copy)]
@@ -59,6 +61,28 @@ pub fn format_size(bytes: u64) -> String {
     }
 }
 
+/// Parse a byte size such as `4096`, `512K`, `10M` or `2G` (powers of 1024)
+pub fn parse_size(s: &str) -> Result<u64, String> {
+    let s = s.trim();
+    let digits = s.find(|c: char| !c.is_ascii_digit()).unwrap_or(s.len());
+    let (number, unit) = s.split_at(digits);
+    let number: u64 = number
+        .parse()
+        .map_err(|_| format!("invalid size '{}': expected a number like 4096 or 10M", s))?;
+
+    let multiplier: u64 = match unit.trim().to_ascii_uppercase().as_str() {
+        "" | "B" => 1,
+        "K" | "KB" | "KIB" => 1024,
+        "M" | "MB" | "MIB" => 1024 * 1024,
+        "G" | "GB" | "GIB" => 1024 * 1024 * 1024,
+        _ => return Err(format!("invalid size unit '{}' (use K, M or G)", unit)),
+    };
+
+    number
+        .checked_mul(multiplier)
+        .ok_or_else(|| format!("size '{}' is too large", s))
+}
+
 /// Format duration in human readable format
 #[allow(dead_code)]
 pub fn format_duration(secs: f64) -> String {
@@ -71,6 +95,44 @@ pub fn format_duration(secs: f64) -> String {
     }
 }
 
+/// Terminal column width of a string
+///
+/// Guest data (hostnames, package descriptions, file names) is often
+/// non-ASCII; byte length over-counts multi-byte characters and under-counts
+/// wide CJK characters.
+pub fn display_width(s: &str) -> usize {
+    UnicodeWidthStr::width(s)
+}
+
+/// Truncate to at most `max_width` columns, ending with "..." when cut
+///
+/// Never splits a character, unlike slicing by byte index.
+pub fn truncate_display(s: &str, max_width: usize) -> String {
+    if display_width(s) <= max_width {
+        return s.to_string();
+    }
+
+    let budget = max_width.saturating_sub(3);
+    let mut width = 0;
+    let mut out = String::new();
+    for c in s.chars() {
+        let w = c.width().unwrap_or(0);
+        if width + w > budget {
+            break;
+        }
+        width += w;
+        out.push(c);
+    }
+    out.push_str("...");
+    out
+}
+
+/// Left-align `s` in a field of `width` columns
+pub fn pad_display(s: &str, width: usize) -> String {
+    let padding = width.saturating_sub(display_width(s));
+    format!("{}{}", s, " ".repeat(padding))
+}
+
 /// Table formatter for aligned output
 #[allow(dead_code)]
 pub struct Table {
@@ -99,19 +161,19 @@ impl Table {
         }
 
         // Calculate column widths
-        let mut widths: Vec<usize> = self.headers.iter().map(|h| h.len()).collect();
+        let mut widths: Vec<usize> = self.headers.iter().map(|h| display_width(h)).collect();
 
         for row in &self.rows {
             for (i, cell) in row.iter().enumerate() {
                 if i < widths.len() {
-                    widths[i] = widths[i].max(cell.len());
+                    widths[i] = widths[i].max(display_width(cell));
                 }
             }
         }
 
         // Print header
         for (i, header) in self.headers.iter().enumerate() {
-            print!("{:width$}  ", header, width = widths[i]);
+            print!("{}  ", pad_display(header, widths[i]));
         }
         println!();
 
@@ -125,7 +187,7 @@ impl Table {
         for row in &self.rows {
             for (i, cell) in row.iter().enumerate() {
                 if i < widths.len() {
-                    print!("{:width$}  ", cell, width = widths[i]);
+                    print!("{}  ", pad_display(cell, widths[i]));
                 }
             }
             println!();
@@ -210,12 +272,34 @@ mod tests {
         assert_eq!(format_size(1024 * 1024 * 1024), "1.00 GB");
     }
 
+    #[test]
+    fn test_parse_size() {
+        assert_eq!(parse_size("4096"), Ok(4096));
+        assert_eq!(parse_size("512K"), Ok(512 * 1024));
+        assert_eq!(parse_size("10M"), Ok(10 * 1024 * 1024));
+        assert_eq!(parse_size("2gib"), Ok(2 * 1024 * 1024 * 1024));
+        assert_eq!(parse_size("0"), Ok(0));
+        assert!(parse_size("ten").is_err());
+        assert!(parse_size("5X").is_err());
+    }
+
     #[test]
     fn test_format_duration() {
         assert_eq!(format_duration(30.5), "30.50s");
         assert_eq!(format_duration(90.0), "1m 30.00s");
         assert_eq!(format_duration(150.75), "2m 30.75s");
     }
+
+    #[test]
+    fn test_truncate_display_non_ascii() {
+        assert_eq!(truncate_display("nginx", 10), "nginx");
+        // Multi-byte characters must not be split
+        assert_eq!(truncate_display("überprüfung-paket", 8), "überp...");
+        // Wide characters take two columns each
+        assert_eq!(display_width("サーバー"), 8);
+        assert_eq!(truncate_display("サーバー設定", 9), "サーバ...");
+        assert_eq!(pad_display("ñ", 3), "ñ  ");
+    }
 }
 
 /// Colorized output helpers
@@ -230,22 +314,22 @@ pub mod colors {
 
     /// Print success message with green checkmark
     pub fn success(msg: &str) {
-        println!("{} {}", "✓".green(), msg.green());
+        println!("{} {}", plain::text("✓").green(), msg.green());
     }
 
     /// Print error message with red X
     pub fn error(msg: &str) {
-        eprintln!("{} {}", "✗".red(), msg.red());
+        eprintln!("{} {}", plain::text("✗").red(), msg.red());
     }
 
     /// Print warning message with yellow triangle
     pub fn warning(msg: &str) {
-        println!("{} {}", "⚠".yellow(), msg.yellow());
+        println!("{} {}", plain::text("⚠").yellow(), msg.yellow());
     }
 
     /// Print info message with coral orange info icon
     pub fn info(msg: &str) {
-        println!("{} {}", "ℹ".truecolor(ORANGE_RGB.0, ORANGE_RGB.1, ORANGE_RGB.2), msg);
+        println!("{} {}", plain::text("ℹ").truecolor(ORANGE_RGB.0, ORANGE_RGB.1, ORANGE_RGB.2), msg);
     }
 
     /// Print header with bold and underline in coral orange
@@ -303,12 +387,12 @@ pub mod colors {
     pub fn status(label: &str, status: Status) {
         let label_colored = label.truecolor(LIGHT_ORANGE_RGB.0, LIGHT_ORANGE_RGB.1, LIGHT_ORANGE_RGB.2);
         match status {
-            Status::Enabled => println!("{} {}: {}", "✓".bold(), label_colored, "enabled".green()),
-            Status::Disabled => println!("{} {}: {}", "✗".bold(), label_colored, "disabled".red()),
+            Status::Enabled => println!("{} {}: {}", plain::text("✓").bold(), label_colored, "enabled".green()),
+            Status::Disabled => println!("{} {}: {}", plain::text("✗").bold(), label_colored, "disabled".red()),
             Status::Unknown => println!("{} {}: {}", "?".bold(), label_colored, "unknown".yellow()),
-            Status::Running => println!("{} {}: {}", "▶".bold(), label_colored, "running".green()),
-            Status::Stopped => println!("{} {}: {}", "■".bold(), label_colored, "stopped".red()),
-            Status::Warning => println!("{} {}: {}", "⚠".bold(), label_colored, "warning".yellow()),
+            Status::Running => println!("{} {}: {}", plain::text("▶").bold(), label_colored, "running".green()),
+            Status::Stopped => println!("{} {}: {}", plain::text("■").bold(), label_colored, "stopped".red()),
+            Status::Warning => println!("{} {}: {}", plain::text("⚠").bold(), label_colored, "warning".yellow()),
         }
     }
 
@@ -325,17 +409,17 @@ pub mod colors {
 
     /// Print a separator line
     pub fn separator() {
-        println!("{}", "─".repeat(80).dimmed());
+        println!("{}", plain::text("─").repeat(80).dimmed());
     }
 
     /// Print a thick separator
     pub fn thick_separator() {
-        println!("{}", "═".repeat(80).bold());
+        println!("{}", plain::text("═").repeat(80).bold());
     }
 
     /// Print bullet point item
     pub fn bullet(msg: &str) {
-        println!("  {} {}", "•".truecolor(ORANGE_RGB.0, ORANGE_RGB.1, ORANGE_RGB.2), msg);
+        println!("  {} {}", plain::text("•").truecolor(ORANGE_RGB.0, ORANGE_RGB.1, ORANGE_RGB.2), msg);
     }
 
     /// Print numbered item
@@ -0,0 +1,245 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Package manager history and automatic update analysis module
+
+pub mod parser;
+pub mod reporter;
+pub mod scanner;
+
+use crate::cli::plain;
+use crate::cli::target;
+use anyhow::Result;
+use guestkit::Guestfs;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::path::Path;
+
+/// Kind of package change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PackageAction {
+    Install,
+    Upgrade,
+    Downgrade,
+    Reinstall,
+    Remove,
+    Purge,
+}
+
+impl PackageAction {
+    pub fn emoji(&self) -> &str {
+        match self {
+            Self::Install => "➕",
+            Self::Upgrade => "⬆️",
+            Self::Downgrade => "⬇️",
+            Self::Reinstall => "🔁",
+            Self::Remove => "➖",
+            Self::Purge => "🗑️",
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Install => "install",
+            Self::Upgrade => "upgrade",
+            Self::Downgrade => "downgrade",
+            Self::Reinstall => "reinstall",
+            Self::Remove => "remove",
+            Self::Purge => "purge",
+        }
+    }
+
+    pub fn is_removal(&self) -> bool {
+        matches!(self, Self::Remove | Self::Purge)
+    }
+}
+
+/// A single package change recorded by the package manager
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageEvent {
+    /// Unix seconds
+    pub timestamp: Option<i64>,
+    pub action: PackageAction,
+    pub name: String,
+    pub version: Option<String>,
+    /// Version replaced by an upgrade or downgrade
+    pub previous_version: Option<String>,
+    /// User who initiated the transaction
+    pub user: Option<String>,
+    /// Command line of the transaction
+    pub command: Option<String>,
+    /// History source (apt, dnf, yum)
+    pub source: String,
+    /// Transaction ID, when the source has one
+    pub transaction: Option<String>,
+    /// Started by unattended-upgrades, dnf-automatic or yum-cron
+    pub automatic: bool,
+}
+
+impl PackageEvent {
+    pub fn time_string(&self) -> String {
+        self.timestamp
+            .and_then(|ts| chrono::DateTime::from_timestamp(ts, 0))
+            .map(|dt| dt.format("%Y-%m-%d %H:%M:%S").to_string())
+            .unwrap_or_else(|| "unknown".to_string())
+    }
+
+    /// One-line description, e.g. `openssl 1.1.1f-19 → 1.1.1f-20`
+    pub fn describe(&self) -> String {
+        match (&self.previous_version, &self.version) {
+            (Some(old), Some(new)) => format!("{} {} → {}", self.name, old, new),
+            (None, Some(version)) => format!("{} {}", self.name, version),
+            _ => self.name.clone(),
+        }
+    }
+
+    /// Who and what initiated the change, e.g. `by alice via dnf install httpd`
+    pub fn origin(&self) -> String {
+        let mut parts = Vec::new();
+        if let Some(ref user) = self.user {
+            parts.push(format!("by {}", user));
+        }
+        if let Some(ref command) = self.command {
+            parts.push(format!("via {}", command));
+        } else {
+            parts.push(format!("via {}", self.source));
+        }
+        parts.join(" ")
+    }
+}
+
+/// Automatic update tooling configuration
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AutoUpdateConfig {
+    /// unattended-upgrades, dnf-automatic or yum-cron
+    pub tool: String,
+    pub config_path: String,
+    /// Whether the timer/service/periodic job is enabled
+    pub enabled: bool,
+    /// Updates are installed rather than only downloaded or reported
+    pub applies_updates: bool,
+    pub security_only: bool,
+    pub details: Vec<String>,
+}
+
+/// Package history summary
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PackageHistorySummary {
+    pub total_events: usize,
+    pub installs: usize,
+    pub upgrades: usize,
+    pub removals: usize,
+    pub automatic_events: usize,
+    pub first_event: Option<String>,
+    pub last_event: Option<String>,
+    /// Users ordered by number of changes
+    pub top_users: Vec<(String, usize)>,
+}
+
+/// Package history report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageHistoryReport {
+    pub image_path: String,
+    pub scanned_at: String,
+    /// Events in chronological order
+    pub events: Vec<PackageEvent>,
+    pub auto_updates: Vec<AutoUpdateConfig>,
+    pub summary: PackageHistorySummary,
+    /// Sources that could not be read
+    pub warnings: Vec<String>,
+}
+
+/// Scan disk image for package manager history
+pub fn scan_package_history<P: AsRef<Path>>(
+    image_path: P,
+    days: Option<u32>,
+    package: Option<&str>,
+    verbose: bool,
+) -> Result<PackageHistoryReport> {
+    let image_path_str = image_path.as_ref().display().to_string();
+
+    if verbose {
+        println!("{}Reading package history in: {}", plain::text("📦 "), image_path_str);
+    }
+
+    // Initialize guestfs
+    let mut g = Guestfs::new()?;
+    g.add_drive_opts(&image_path, true, None)?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
+    g.launch()?;
+
+    // Inspect OS
+    let roots = g.inspect_os()?;
+    if roots.is_empty() {
+        anyhow::bail!("No operating systems found in disk image");
+    }
+
+    let root = &roots[0];
+
+    // Mount filesystems
+    g.mount_os_ro(root)?;
+
+    let (mut events, warnings) = scanner::collect_events(&mut g);
+    let auto_updates = scanner::collect_auto_updates(&mut g);
+
+    // Shutdown guestfs
+    g.shutdown()?;
+
+    if let Some(days) = days {
+        let cutoff = chrono::Utc::now().timestamp() - i64::from(days) * 86400;
+        events.retain(|e| e.timestamp.map(|ts| ts >= cutoff).unwrap_or(false));
+    }
+    if let Some(package) = package {
+        events.retain(|e| e.name.contains(package));
+    }
+
+    let summary = summarize(&events);
+
+    Ok(PackageHistoryReport {
+        image_path: image_path_str,
+        scanned_at: chrono::Utc::now().to_rfc3339(),
+        events,
+        auto_updates,
+        summary,
+        warnings,
+    })
+}
+
+/// Build summary statistics for a set of package events
+pub fn summarize(events: &[PackageEvent]) -> PackageHistorySummary {
+    let count = |f: fn(&PackageAction) -> bool| events.iter().filter(|e| f(&e.action)).count();
+
+    let mut users: HashMap<String, usize> = HashMap::new();
+    for user in events.iter().filter_map(|e| e.user.as_ref()) {
+        *users.entry(user.clone()).or_insert(0) += 1;
+    }
+    let mut top_users: Vec<(String, usize)> = users.into_iter().collect();
+    top_users.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+
+    let dated: Vec<&PackageEvent> = events.iter().filter(|e| e.timestamp.is_some()).collect();
+
+    PackageHistorySummary {
+        total_events: events.len(),
+        installs: count(|a| matches!(a, PackageAction::Install)),
+        upgrades: count(|a| matches!(a, PackageAction::Upgrade | PackageAction::Downgrade)),
+        removals: count(PackageAction::is_removal),
+        automatic_events: events.iter().filter(|e| e.automatic).count(),
+        first_event: dated.iter().min_by_key(|e| e.timestamp).map(|e| e.time_string()),
+        last_event: dated.iter().max_by_key(|e| e.timestamp).map(|e| e.time_string()),
+        top_users,
+    }
+}
+
+/// Most recent event per package name
+pub fn latest_by_package(events: &[PackageEvent]) -> HashMap<&str, &PackageEvent> {
+    let mut latest: HashMap<&str, &PackageEvent> = HashMap::new();
+    for event in events {
+        let newer = latest
+            .get(event.name.as_str())
+            .map(|prev| event.timestamp >= prev.timestamp)
+            .unwrap_or(true);
+        if newer {
+            latest.insert(event.name.as_str(), event);
+        }
+    }
+    latest
+}
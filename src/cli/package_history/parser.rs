@@ -0,0 +1,475 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Parsers for package manager history sources
+
+use super::{PackageAction, PackageEvent};
+use chrono::{NaiveDateTime, TimeZone, Utc};
+use regex::Regex;
+use std::collections::HashMap;
+
+/// Query for the dnf history database (/var/lib/dnf/history.sqlite)
+pub const DNF_HISTORY_QUERY: &str = "SELECT t.id, t.dt_begin, t.user_id, \
+     replace(replace(ifnull(t.cmdline, ''), char(9), ' '), char(10), ' '), \
+     ti.action, r.name, r.epoch, r.version, r.release \
+     FROM trans t JOIN trans_item ti ON ti.trans_id = t.id \
+     JOIN rpm r ON r.item_id = ti.item_id ORDER BY t.id";
+
+/// Query for yum history databases (/var/lib/yum/history/history-*.sqlite)
+pub const YUM_HISTORY_QUERY: &str = "SELECT b.tid, b.timestamp, b.loginuid, \
+     replace(ifnull((SELECT group_concat(c.cmdline, ' ') FROM trans_cmdline c WHERE c.tid = b.tid), ''), char(9), ' '), \
+     d.state, p.name, p.epoch, p.version, p.release \
+     FROM trans_beg b JOIN trans_data_pkgs d ON d.tid = b.tid \
+     JOIN pkg_tups p ON p.pkgtupid = d.pkgtupid ORDER BY b.tid";
+
+/// Map a dnf `trans_item.action` code to an action
+///
+/// Codes for the replaced side of a transaction (downgraded, obsoleted,
+/// upgraded, reinstalled) and reason changes are skipped.
+fn dnf_action(code: &str) -> Option<PackageAction> {
+    match code {
+        "1" => Some(PackageAction::Install),
+        "2" => Some(PackageAction::Downgrade),
+        "4" => Some(PackageAction::Install),
+        "6" => Some(PackageAction::Upgrade),
+        "8" => Some(PackageAction::Remove),
+        "9" => Some(PackageAction::Reinstall),
+        _ => None,
+    }
+}
+
+/// Map a yum `trans_data_pkgs.state` to an action
+fn yum_action(state: &str) -> Option<PackageAction> {
+    match state {
+        "Install" | "True-Install" | "Dep-Install" | "Obsoleting" => Some(PackageAction::Install),
+        "Update" => Some(PackageAction::Upgrade),
+        "Downgrade" => Some(PackageAction::Downgrade),
+        "Reinstall" => Some(PackageAction::Reinstall),
+        "Erase" => Some(PackageAction::Remove),
+        _ => None,
+    }
+}
+
+/// Parse tab-separated rows from [`DNF_HISTORY_QUERY`] or [`YUM_HISTORY_QUERY`]
+pub fn parse_history_rows(
+    output: &str,
+    source: &str,
+    users: &HashMap<u32, String>,
+) -> Vec<PackageEvent> {
+    let mut events = Vec::new();
+
+    for line in output.lines() {
+        let fields: Vec<&str> = line.split('\t').collect();
+        if fields.len() < 9 {
+            continue;
+        }
+
+        let action = if source == "yum" {
+            yum_action(fields[4])
+        } else {
+            dnf_action(fields[4])
+        };
+        let Some(action) = action else {
+            continue;
+        };
+
+        let command = Some(fields[3].trim().to_string()).filter(|c| !c.is_empty());
+        let epoch = fields[6];
+        let version = if epoch.is_empty() || epoch == "0" {
+            format!("{}-{}", fields[7], fields[8])
+        } else {
+            format!("{}:{}-{}", epoch, fields[7], fields[8])
+        };
+
+        events.push(PackageEvent {
+            timestamp: fields[1].parse().ok(),
+            action,
+            name: fields[5].to_string(),
+            version: Some(version),
+            previous_version: None,
+            user: fields[2].parse::<u32>().ok().map(|uid| user_name(uid, users)),
+            automatic: is_automatic(command.as_deref()),
+            command,
+            source: source.to_string(),
+            transaction: Some(fields[0].to_string()),
+        });
+    }
+
+    events
+}
+
+/// Parse an apt history log (/var/log/apt/history.log)
+pub fn parse_apt_history(content: &str) -> Vec<PackageEvent> {
+    let entry_re = Regex::new(r"([^\s,()]+) \(([^)]*)\)").unwrap();
+    let mut events = Vec::new();
+
+    for block in content.split("Start-Date:").skip(1) {
+        let mut timestamp = None;
+        let mut command = None;
+        let mut user = None;
+        let mut actions: Vec<(PackageAction, &str)> = Vec::new();
+
+        for (i, line) in block.lines().enumerate() {
+            if i == 0 {
+                timestamp = parse_apt_date(line.trim());
+                continue;
+            }
+
+            let Some((key, value)) = line.split_once(": ") else {
+                continue;
+            };
+
+            match key {
+                "Commandline" => command = Some(value.trim().to_string()),
+                "Requested-By" => {
+                    user = value.split_whitespace().next().map(|u| u.to_string());
+                }
+                "Install" => actions.push((PackageAction::Install, value)),
+                "Upgrade" => actions.push((PackageAction::Upgrade, value)),
+                "Downgrade" => actions.push((PackageAction::Downgrade, value)),
+                "Reinstall" => actions.push((PackageAction::Reinstall, value)),
+                "Remove" => actions.push((PackageAction::Remove, value)),
+                "Purge" => actions.push((PackageAction::Purge, value)),
+                _ => {}
+            }
+        }
+
+        // Transactions started without a terminal belong to root
+        if user.is_none() && command.is_some() {
+            user = Some("root".to_string());
+        }
+
+        for (action, list) in actions {
+            for caps in entry_re.captures_iter(list) {
+                let name = caps[1].split(':').next().unwrap_or(&caps[1]).to_string();
+                let versions: Vec<&str> = caps[2]
+                    .split(", ")
+                    .filter(|v| *v != "automatic")
+                    .collect();
+
+                let (previous_version, version) = match (action, versions.as_slice()) {
+                    (PackageAction::Upgrade | PackageAction::Downgrade, [old, new, ..]) => {
+                        (Some(old.to_string()), Some(new.to_string()))
+                    }
+                    (_, [v, ..]) => (None, Some(v.to_string())),
+                    _ => (None, None),
+                };
+
+                events.push(PackageEvent {
+                    timestamp,
+                    action,
+                    name,
+                    version,
+                    previous_version,
+                    user: user.clone(),
+                    automatic: is_automatic(command.as_deref()),
+                    command: command.clone(),
+                    source: "apt".to_string(),
+                    transaction: None,
+                });
+            }
+        }
+    }
+
+    events
+}
+
+/// Parse a dnf rpm log (/var/log/dnf.rpm.log), used when the history DB is unreadable
+pub fn parse_dnf_rpm_log(content: &str) -> Vec<PackageEvent> {
+    let mut events = Vec::new();
+
+    for line in content.lines() {
+        let mut parts = line.splitn(3, ' ');
+        let (Some(ts), Some(_level), Some(rest)) = (parts.next(), parts.next(), parts.next()) else {
+            continue;
+        };
+
+        let Some((verb, nevra)) = rest.split_once(": ") else {
+            continue;
+        };
+
+        let action = match verb {
+            "Installed" | "Install" => PackageAction::Install,
+            "Upgrade" => PackageAction::Upgrade,
+            "Downgrade" => PackageAction::Downgrade,
+            "Reinstall" => PackageAction::Reinstall,
+            "Erase" | "Removed" => PackageAction::Remove,
+            _ => continue,
+        };
+
+        let (name, version) = split_nevra(nevra.trim());
+        events.push(PackageEvent {
+            timestamp: chrono::DateTime::parse_from_str(ts, "%Y-%m-%dT%H:%M:%S%z")
+                .ok()
+                .map(|dt| dt.timestamp()),
+            action,
+            name,
+            version,
+            previous_version: None,
+            user: None,
+            automatic: false,
+            command: None,
+            source: "dnf".to_string(),
+            transaction: None,
+        });
+    }
+
+    events
+}
+
+/// Parse a yum log (/var/log/yum.log); entries carry no year, so `year` is supplied
+pub fn parse_yum_log(content: &str, year: i32) -> Vec<PackageEvent> {
+    let mut events = Vec::new();
+
+    for line in content.lines() {
+        // "Jan 05 10:11:12 Installed: nginx-1.20.1-1.el7.x86_64"
+        if line.len() < 16 {
+            continue;
+        }
+        let (date, rest) = line.split_at(15);
+        let Some((verb, target)) = rest.trim().split_once(": ") else {
+            continue;
+        };
+
+        let action = match verb {
+            "Installed" => PackageAction::Install,
+            "Updated" => PackageAction::Upgrade,
+            "Erased" => PackageAction::Remove,
+            _ => continue,
+        };
+
+        let (name, version) = if action == PackageAction::Remove {
+            (target.trim().to_string(), None)
+        } else {
+            split_nevra(target.trim())
+        };
+
+        let timestamp = NaiveDateTime::parse_from_str(&format!("{} {}", year, date), "%Y %b %d %H:%M:%S")
+            .ok()
+            .map(|dt| Utc.from_utc_datetime(&dt).timestamp());
+
+        events.push(PackageEvent {
+            timestamp,
+            action,
+            name,
+            version,
+            previous_version: None,
+            user: None,
+            automatic: false,
+            command: None,
+            source: "yum".to_string(),
+            transaction: None,
+        });
+    }
+
+    events
+}
+
+/// Build a uid -> user name map from /etc/passwd
+pub fn parse_passwd(content: &str) -> HashMap<u32, String> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            let uid = fields.get(2)?.parse().ok()?;
+            Some((uid, fields[0].to_string()))
+        })
+        .collect()
+}
+
+/// Parse `APT::Periodic::*` settings from apt.conf snippets
+pub fn parse_apt_periodic(content: &str) -> HashMap<String, String> {
+    let re = Regex::new(r#"APT::Periodic::([A-Za-z-]+)\s+"([^"]*)"\s*;"#).unwrap();
+    re.captures_iter(content)
+        .filter(|caps| !is_commented(content, caps.get(0).map(|m| m.start()).unwrap_or(0)))
+        .map(|caps| (caps[1].to_string(), caps[2].to_string()))
+        .collect()
+}
+
+/// Parse `Unattended-Upgrade::Allowed-Origins` / `Origins-Pattern` entries
+pub fn parse_unattended_origins(content: &str) -> Vec<String> {
+    let mut origins = Vec::new();
+    let mut in_list = false;
+
+    for line in content.lines() {
+        let line = line.split("//").next().unwrap_or("").trim();
+        if line.starts_with("Unattended-Upgrade::Allowed-Origins")
+            || line.starts_with("Unattended-Upgrade::Origins-Pattern")
+        {
+            in_list = true;
+            continue;
+        }
+        if in_list {
+            if line.starts_with('}') {
+                in_list = false;
+                continue;
+            }
+            let origin = line.trim_end_matches(';').trim().trim_matches('"');
+            if !origin.is_empty() {
+                origins.push(origin.to_string());
+            }
+        }
+    }
+
+    origins
+}
+
+/// Look up a value in an INI-style file such as /etc/dnf/automatic.conf
+pub fn parse_ini_value(content: &str, section: &str, key: &str) -> Option<String> {
+    let mut current = String::new();
+
+    for line in content.lines() {
+        let line = line.trim();
+        if line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if line.starts_with('[') && line.ends_with(']') {
+            current = line[1..line.len() - 1].trim().to_string();
+            continue;
+        }
+        if current == section {
+            if let Some((k, v)) = line.split_once('=') {
+                if k.trim() == key {
+                    return Some(v.trim().to_string());
+                }
+            }
+        }
+    }
+
+    None
+}
+
+/// Split `name-[epoch:]version-release.arch` into name and version-release
+fn split_nevra(nevra: &str) -> (String, Option<String>) {
+    const ARCHES: &[&str] = &[
+        "x86_64", "i686", "i386", "noarch", "aarch64", "ppc64le", "s390x", "armv7hl", "src",
+    ];
+
+    let without_arch = match nevra.rsplit_once('.') {
+        Some((rest, arch)) if ARCHES.contains(&arch) => rest,
+        _ => nevra,
+    };
+
+    let mut parts = without_arch.rsplitn(3, '-');
+    match (parts.next(), parts.next(), parts.next()) {
+        (Some(release), Some(version), Some(name)) => {
+            (name.to_string(), Some(format!("{}-{}", version, release)))
+        }
+        _ => (without_arch.to_string(), None),
+    }
+}
+
+fn parse_apt_date(value: &str) -> Option<i64> {
+    let normalized = value.split_whitespace().collect::<Vec<_>>().join(" ");
+    NaiveDateTime::parse_from_str(&normalized, "%Y-%m-%d %H:%M:%S")
+        .ok()
+        .map(|dt| Utc.from_utc_datetime(&dt).timestamp())
+}
+
+fn user_name(uid: u32, users: &HashMap<u32, String>) -> String {
+    users.get(&uid).cloned().unwrap_or_else(|| format!("uid {}", uid))
+}
+
+/// Whether a transaction was started by an automatic update tool
+fn is_automatic(command: Option<&str>) -> bool {
+    command
+        .map(|c| c.contains("unattended-upgrade") || c.contains("dnf-automatic") || c.contains("yum-cron"))
+        .unwrap_or(false)
+}
+
+/// Whether the match at `pos` sits on a `//` commented line
+fn is_commented(content: &str, pos: usize) -> bool {
+    let line_start = content[..pos].rfind('\n').map(|i| i + 1).unwrap_or(0);
+    content[line_start..pos].contains("//")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const APT_HISTORY: &str = "
+Start-Date: 2024-01-05  10:11:12
+Commandline: apt-get install -y nginx
+Requested-By: ubuntu (1000)
+Install: nginx:amd64 (1.18.0-0ubuntu1), libnginx-mod-http:amd64 (1.18.0-0ubuntu1, automatic)
+End-Date: 2024-01-05  10:11:20
+
+Start-Date: 2024-01-06  06:30:01
+Commandline: /usr/bin/unattended-upgrade
+Upgrade: openssl:amd64 (1.1.1f-1ubuntu2.19, 1.1.1f-1ubuntu2.20)
+End-Date: 2024-01-06  06:30:09
+";
+
+    #[test]
+    fn test_parse_apt_history() {
+        let events = parse_apt_history(APT_HISTORY);
+        assert_eq!(events.len(), 3);
+
+        assert_eq!(events[0].name, "nginx");
+        assert_eq!(events[0].action, PackageAction::Install);
+        assert_eq!(events[0].user.as_deref(), Some("ubuntu"));
+        assert_eq!(events[1].version.as_deref(), Some("1.18.0-0ubuntu1"));
+
+        let upgrade = &events[2];
+        assert_eq!(upgrade.action, PackageAction::Upgrade);
+        assert_eq!(upgrade.previous_version.as_deref(), Some("1.1.1f-1ubuntu2.19"));
+        assert_eq!(upgrade.version.as_deref(), Some("1.1.1f-1ubuntu2.20"));
+        assert!(upgrade.automatic);
+        assert_eq!(upgrade.user.as_deref(), Some("root"));
+        assert!(upgrade.timestamp.unwrap() > events[0].timestamp.unwrap());
+    }
+
+    #[test]
+    fn test_parse_dnf_history_rows() {
+        let users = parse_passwd("root:x:0:0:root:/root:/bin/bash\nalice:x:1000:1000::/home/alice:/bin/bash\n");
+        let rows = "3\t1704449472\t1000\tinstall httpd\t1\thttpd\t0\t2.4.57\t5.el9\n\
+                    4\t1704535872\t0\tupgrade\t7\tbash\t0\t5.1.8\t4.el9\n\
+                    4\t1704535872\t0\tupgrade\t6\tbash\t0\t5.1.8\t6.el9\n";
+
+        let events = parse_history_rows(rows, "dnf", &users);
+        assert_eq!(events.len(), 2);
+        assert_eq!(events[0].user.as_deref(), Some("alice"));
+        assert_eq!(events[0].command.as_deref(), Some("install httpd"));
+        assert_eq!(events[1].action, PackageAction::Upgrade);
+        assert_eq!(events[1].version.as_deref(), Some("5.1.8-6.el9"));
+        assert_eq!(events[1].transaction.as_deref(), Some("4"));
+    }
+
+    #[test]
+    fn test_parse_rpm_logs() {
+        let dnf = parse_dnf_rpm_log(
+            "2024-01-05T10:11:12+0000 SUBDEBUG Installed: httpd-2.4.57-5.el9.x86_64\n\
+             2024-01-05T10:11:12+0000 INFO --- logging initialized ---\n\
+             2024-01-06T10:00:00+0000 SUBDEBUG Erase: vim-minimal-2:8.2.2637-20.el9.x86_64\n",
+        );
+        assert_eq!(dnf.len(), 2);
+        assert_eq!(dnf[0].name, "httpd");
+        assert_eq!(dnf[0].version.as_deref(), Some("2.4.57-5.el9"));
+        assert_eq!(dnf[1].name, "vim-minimal");
+        assert_eq!(dnf[1].action, PackageAction::Remove);
+
+        let yum = parse_yum_log(
+            "Jan 05 10:11:12 Installed: nginx-1.20.1-1.el7.x86_64\nJan 06 09:00:00 Erased: telnet\n",
+            2023,
+        );
+        assert_eq!(yum.len(), 2);
+        assert_eq!(yum[0].name, "nginx");
+        assert_eq!(yum[1].name, "telnet");
+        assert!(yum[0].timestamp.is_some());
+    }
+
+    #[test]
+    fn test_parse_auto_update_configs() {
+        let periodic = parse_apt_periodic(
+            "APT::Periodic::Update-Package-Lists \"1\";\n// APT::Periodic::Unattended-Upgrade \"0\";\nAPT::Periodic::Unattended-Upgrade \"1\";\n",
+        );
+        assert_eq!(periodic.get("Unattended-Upgrade").map(String::as_str), Some("1"));
+
+        let origins = parse_unattended_origins(
+            "Unattended-Upgrade::Allowed-Origins {\n\t\"${distro_id}:${distro_codename}-security\";\n//\t\"${distro_id}:${distro_codename}-updates\";\n};\n",
+        );
+        assert_eq!(origins, vec!["${distro_id}:${distro_codename}-security"]);
+
+        let conf = "[commands]\nupgrade_type = security\napply_updates = yes\n[emitters]\napply_updates = no\n";
+        assert_eq!(parse_ini_value(conf, "commands", "apply_updates").as_deref(), Some("yes"));
+        assert_eq!(parse_ini_value(conf, "commands", "missing"), None);
+    }
+}
@@ -0,0 +1,122 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Package history report formatting
+
+use super::PackageHistoryReport;
+
+/// Number of most recent events shown in the text report
+const RECENT_EVENTS: usize = 50;
+
+/// Format package history report as text
+pub fn format_report(report: &PackageHistoryReport) -> String {
+    let mut output = String::new();
+
+    output.push_str("📦 Package History Report\n");
+    output.push_str("=========================\n\n");
+    output.push_str(&format!("Image: {}\n", report.image_path));
+    output.push_str(&format!("Scanned: {}\n\n", report.scanned_at));
+
+    // Summary
+    let summary = &report.summary;
+    output.push_str("📊 Summary\n");
+    output.push_str("----------\n");
+    output.push_str(&format!("Total changes: {}\n", summary.total_events));
+    output.push_str(&format!("Installs: {}\n", summary.installs));
+    output.push_str(&format!("Upgrades/downgrades: {}\n", summary.upgrades));
+    output.push_str(&format!("Removals: {}\n", summary.removals));
+    output.push_str(&format!("Automatic: {}\n", summary.automatic_events));
+    if let Some(ref first) = summary.first_event {
+        output.push_str(&format!("First change: {}\n", first));
+    }
+    if let Some(ref last) = summary.last_event {
+        output.push_str(&format!("Last change: {}\n", last));
+    }
+    output.push('\n');
+
+    if !summary.top_users.is_empty() {
+        output.push_str("👤 Initiated By\n");
+        output.push_str("---------------\n");
+        for (user, count) in summary.top_users.iter().take(10) {
+            output.push_str(&format!("{}: {}\n", user, count));
+        }
+        output.push('\n');
+    }
+
+    // Automatic updates
+    output.push_str("🔄 Automatic Updates\n");
+    output.push_str("--------------------\n");
+    if report.auto_updates.is_empty() {
+        output.push_str("No automatic update tooling installed\n");
+    }
+    for config in &report.auto_updates {
+        let state = match (config.enabled, config.applies_updates) {
+            (true, true) => "✅ enabled, installs updates",
+            (true, false) => "⚠️  enabled, download/notify only",
+            (false, _) => "❌ disabled",
+        };
+        output.push_str(&format!("{}: {}", config.tool, state));
+        if config.security_only {
+            output.push_str(" (security only)");
+        }
+        output.push('\n');
+        for detail in &config.details {
+            output.push_str(&format!("   └─ {}\n", detail));
+        }
+    }
+    output.push('\n');
+
+    // Recent changes, newest first
+    if !report.events.is_empty() {
+        output.push_str(&format!("📅 Recent Changes (last {})\n", RECENT_EVENTS.min(report.events.len())));
+        output.push_str("-------------------------\n");
+        for event in report.events.iter().rev().take(RECENT_EVENTS) {
+            output.push_str(&format!(
+                "{} {} [{}] {}",
+                event.action.emoji(),
+                event.time_string(),
+                event.action.as_str(),
+                event.describe()
+            ));
+            if event.automatic {
+                output.push_str(" (automatic)");
+            }
+            output.push('\n');
+            output.push_str(&format!("   └─ {}\n", event.origin()));
+        }
+        output.push('\n');
+    }
+
+    if !report.warnings.is_empty() {
+        output.push_str("⚠️  Warnings\n");
+        output.push_str("-----------\n");
+        for warning in &report.warnings {
+            output.push_str(&format!("{}\n", warning));
+        }
+        output.push('\n');
+    }
+
+    output
+}
+
+/// Format package history as CSV
+pub fn format_csv(report: &PackageHistoryReport) -> String {
+    let mut output =
+        String::from("Time,Action,Package,Version,PreviousVersion,User,Source,Transaction,Automatic,Command\n");
+
+    for event in &report.events {
+        output.push_str(&format!(
+            "{},{},\"{}\",\"{}\",\"{}\",\"{}\",{},{},{},\"{}\"\n",
+            event.time_string(),
+            event.action.as_str(),
+            event.name,
+            event.version.as_deref().unwrap_or(""),
+            event.previous_version.as_deref().unwrap_or(""),
+            event.user.as_deref().unwrap_or(""),
+            event.source,
+            event.transaction.as_deref().unwrap_or(""),
+            event.automatic,
+            event.command.as_deref().unwrap_or("").replace('"', "\"\"")
+        ));
+    }
+
+    output
+}
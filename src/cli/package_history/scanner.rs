@@ -0,0 +1,256 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Package history collection from a mounted guest
+
+use super::parser;
+use super::{AutoUpdateConfig, PackageEvent};
+use chrono::Datelike;
+use guestkit::Guestfs;
+use std::collections::HashMap;
+use std::process::Command;
+
+const DNF_HISTORY_DB: &str = "/var/lib/dnf/history.sqlite";
+const YUM_HISTORY_DIR: &str = "/var/lib/yum/history";
+const APT_LOG_DIR: &str = "/var/log/apt";
+
+/// Collect package events from every history source found in the guest
+///
+/// Returns events in chronological order, plus warnings for sources that
+/// exist but could not be read.
+pub fn collect_events(g: &mut Guestfs) -> (Vec<PackageEvent>, Vec<String>) {
+    let mut events = Vec::new();
+    let mut warnings = Vec::new();
+
+    let users = read_text(g, "/etc/passwd")
+        .map(|content| parser::parse_passwd(&content))
+        .unwrap_or_default();
+
+    // dnf: history database, falling back to the rpm log
+    let mut dnf_events = Vec::new();
+    if g.is_file(DNF_HISTORY_DB).unwrap_or(false) {
+        match query_history_db(g, DNF_HISTORY_DB, parser::DNF_HISTORY_QUERY) {
+            Ok(rows) => dnf_events = parser::parse_history_rows(&rows, "dnf", &users),
+            Err(e) => warnings.push(format!("{}: {}", DNF_HISTORY_DB, e)),
+        }
+    }
+    if dnf_events.is_empty() {
+        if let Some(content) = read_text(g, "/var/log/dnf.rpm.log") {
+            dnf_events = parser::parse_dnf_rpm_log(&content);
+        }
+    }
+    events.extend(dnf_events);
+
+    // yum: per-day history databases, falling back to yum.log
+    let mut yum_events = Vec::new();
+    for db in list_files(g, YUM_HISTORY_DIR, ".sqlite") {
+        match query_history_db(g, &db, parser::YUM_HISTORY_QUERY) {
+            Ok(rows) => yum_events.extend(parser::parse_history_rows(&rows, "yum", &users)),
+            Err(e) => warnings.push(format!("{}: {}", db, e)),
+        }
+    }
+    if yum_events.is_empty() {
+        if let Some(content) = read_text(g, "/var/log/yum.log") {
+            let year = g
+                .stat("/var/log/yum.log")
+                .ok()
+                .and_then(|st| chrono::DateTime::from_timestamp(st.mtime, 0))
+                .map(|dt| dt.year())
+                .unwrap_or_else(|| chrono::Utc::now().year());
+            yum_events = parser::parse_yum_log(&content, year);
+        }
+    }
+    events.extend(yum_events);
+
+    // apt: current and rotated history logs
+    for log in list_files(g, APT_LOG_DIR, "") {
+        let name = log.rsplit('/').next().unwrap_or("");
+        if !name.starts_with("history.log") {
+            continue;
+        }
+
+        let content = if name.ends_with(".gz") {
+            g.zgrep(".", &log).ok().map(|lines| lines.join("\n"))
+        } else {
+            read_text(g, &log)
+        };
+
+        match content {
+            Some(content) => events.extend(parser::parse_apt_history(&content)),
+            None => warnings.push(format!("{}: unreadable", log)),
+        }
+    }
+
+    events.sort_by_key(|e| e.timestamp);
+    (events, warnings)
+}
+
+/// Inspect unattended-upgrades, dnf-automatic and yum-cron configuration
+pub fn collect_auto_updates(g: &mut Guestfs) -> Vec<AutoUpdateConfig> {
+    let mut configs = Vec::new();
+
+    // unattended-upgrades (Debian/Ubuntu)
+    if g.exists("/usr/bin/unattended-upgrade").unwrap_or(false) {
+        let mut periodic = HashMap::new();
+        for conf in ["/etc/apt/apt.conf.d/10periodic", "/etc/apt/apt.conf.d/20auto-upgrades"] {
+            if let Some(content) = read_text(g, conf) {
+                periodic.extend(parser::parse_apt_periodic(&content));
+            }
+        }
+
+        let policy_path = "/etc/apt/apt.conf.d/50unattended-upgrades";
+        let policy = read_text(g, policy_path).unwrap_or_default();
+        let origins = parser::parse_unattended_origins(&policy);
+
+        let enabled = periodic
+            .get("Unattended-Upgrade")
+            .map(|v| v != "0")
+            .unwrap_or(false);
+
+        let mut details: Vec<String> = periodic
+            .iter()
+            .map(|(k, v)| format!("APT::Periodic::{} = {}", k, v))
+            .collect();
+        details.sort();
+        details.extend(origins.iter().map(|o| format!("origin: {}", o)));
+        if policy.contains("Automatic-Reboot \"true\"") {
+            details.push("automatic reboot enabled".to_string());
+        }
+
+        configs.push(AutoUpdateConfig {
+            tool: "unattended-upgrades".to_string(),
+            config_path: policy_path.to_string(),
+            enabled,
+            applies_updates: enabled,
+            security_only: !origins.is_empty() && origins.iter().all(|o| o.contains("security")),
+            details,
+        });
+    }
+
+    // dnf-automatic (Fedora/RHEL 8+)
+    let dnf_conf = "/etc/dnf/automatic.conf";
+    if let Some(content) = read_text(g, dnf_conf) {
+        let timers = [
+            "dnf-automatic.timer",
+            "dnf-automatic-install.timer",
+            "dnf-automatic-download.timer",
+            "dnf-automatic-notifyonly.timer",
+        ];
+        let enabled_timers: Vec<&str> = timers
+            .iter()
+            .copied()
+            .filter(|t| {
+                g.exists(&format!("/etc/systemd/system/timers.target.wants/{}", t))
+                    .unwrap_or(false)
+            })
+            .collect();
+
+        let apply = parser::parse_ini_value(&content, "commands", "apply_updates");
+        let upgrade_type = parser::parse_ini_value(&content, "commands", "upgrade_type");
+
+        let applies_updates = enabled_timers.contains(&"dnf-automatic-install.timer")
+            || (enabled_timers.contains(&"dnf-automatic.timer") && is_yes(apply.as_deref()));
+
+        let mut details: Vec<String> = enabled_timers.iter().map(|t| format!("timer: {}", t)).collect();
+        if let Some(ref apply) = apply {
+            details.push(format!("apply_updates = {}", apply));
+        }
+        if let Some(ref upgrade_type) = upgrade_type {
+            details.push(format!("upgrade_type = {}", upgrade_type));
+        }
+
+        configs.push(AutoUpdateConfig {
+            tool: "dnf-automatic".to_string(),
+            config_path: dnf_conf.to_string(),
+            enabled: !enabled_timers.is_empty(),
+            applies_updates,
+            security_only: upgrade_type.as_deref() == Some("security"),
+            details,
+        });
+    }
+
+    // yum-cron (RHEL/CentOS 7)
+    let yum_conf = "/etc/yum/yum-cron.conf";
+    if let Some(content) = read_text(g, yum_conf) {
+        let enabled = g
+            .exists("/etc/systemd/system/multi-user.target.wants/yum-cron.service")
+            .unwrap_or(false);
+        let apply = parser::parse_ini_value(&content, "commands", "apply_updates");
+        let update_cmd = parser::parse_ini_value(&content, "commands", "update_cmd");
+
+        let mut details = Vec::new();
+        if let Some(ref apply) = apply {
+            details.push(format!("apply_updates = {}", apply));
+        }
+        if let Some(ref update_cmd) = update_cmd {
+            details.push(format!("update_cmd = {}", update_cmd));
+        }
+
+        configs.push(AutoUpdateConfig {
+            tool: "yum-cron".to_string(),
+            config_path: yum_conf.to_string(),
+            enabled,
+            applies_updates: enabled && is_yes(apply.as_deref()),
+            security_only: update_cmd.map(|c| c.contains("security")).unwrap_or(false),
+            details,
+        });
+    }
+
+    configs
+}
+
+/// Run a history query against a copy of a guest SQLite database
+///
+/// The database (and any WAL files) is copied out first so the image is
+/// never written to. Requires the `sqlite3` CLI on the host.
+fn query_history_db(g: &mut Guestfs, db: &str, query: &str) -> Result<String, String> {
+    let tmp = tempfile::tempdir().map_err(|e| e.to_string())?;
+    let local = tmp.path().join("history.sqlite");
+    let local_str = local.to_string_lossy().to_string();
+
+    g.download(db, &local_str).map_err(|e| e.to_string())?;
+    for suffix in ["-wal", "-shm"] {
+        let side = format!("{}{}", db, suffix);
+        if g.is_file(&side).unwrap_or(false) {
+            let _ = g.download(&side, &format!("{}{}", local_str, suffix));
+        }
+    }
+
+    let output = Command::new("sqlite3")
+        .arg("-batch")
+        .arg("-separator")
+        .arg("\t")
+        .arg(&local)
+        .arg(query)
+        .output()
+        .map_err(|e| format!("failed to run sqlite3: {}", e))?;
+
+    if !output.status.success() {
+        return Err(String::from_utf8_lossy(&output.stderr).trim().to_string());
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+fn read_text(g: &mut Guestfs, path: &str) -> Option<String> {
+    if !g.is_file(path).unwrap_or(false) {
+        return None;
+    }
+    g.read_file(path)
+        .ok()
+        .map(|bytes| String::from_utf8_lossy(&bytes).to_string())
+}
+
+fn list_files(g: &mut Guestfs, dir: &str, suffix: &str) -> Vec<String> {
+    if !g.is_dir(dir).unwrap_or(false) {
+        return Vec::new();
+    }
+    g.ls(dir)
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|name| name.ends_with(suffix))
+        .map(|name| format!("{}/{}", dir, name))
+        .collect()
+}
+
+fn is_yes(value: Option<&str>) -> bool {
+    matches!(value, Some("yes" | "true" | "1" | "True" | "Yes"))
+}
@@ -29,6 +29,7 @@
 //! }
 //! ```
 
+use crate::cli::plain;
 use guestkit::core::{BinaryCache, CachedInspection, Error, Result};
 use rayon::prelude::*;
 use sha2::{Digest, Sha256};
@@ -175,8 +176,12 @@ impl ParallelInspector {
         let start = Instant::now();
 
         if self.config.verbose {
-            println!("🚀 Starting parallel inspection of {} disks", disk_paths.len());
-            println!("👷 Workers: {}", self.num_workers());
+            println!(
+                "{}Starting parallel inspection of {} disks",
+                plain::text("🚀 "),
+                disk_paths.len()
+            );
+            println!("{}Workers: {}", plain::text("👷 "), self.num_workers());
         }
 
         // Convert paths to PathBuf for parallel processing
@@ -205,7 +210,7 @@ impl ParallelInspector {
         let start = Instant::now();
 
         if self.config.verbose {
-            println!("🔍 Inspecting: {}", disk_path.display());
+            println!("{}Inspecting: {}", plain::text("🔍 "), disk_path.display());
         }
 
         // Inspect with cache integration
@@ -249,7 +254,7 @@ impl ParallelInspector {
             if let Ok(cache) = BinaryCache::new() {
                 if let Ok(cached_data) = cache.load(&cache_key) {
                     if self.config.verbose {
-                        println!("✅ Cache hit: {}", disk_path.display());
+                        println!("{} Cache hit: {}", plain::text("✅"), disk_path.display());
                     }
                     return Ok((
                         cached_data.os_info.os_type,
@@ -262,7 +267,7 @@ impl ParallelInspector {
 
         // Cache miss - perform actual inspection
         if self.config.verbose {
-            println!("🔍 Cache miss - inspecting: {}", disk_path.display());
+            println!("{}Cache miss - inspecting: {}", plain::text("🔍 "), disk_path.display());
         }
 
         // TODO: Replace with actual guestfs inspection
@@ -335,15 +340,15 @@ impl ParallelInspector {
         let failed = results.iter().filter(|r| !r.success).count();
         let from_cache = results.iter().filter(|r| r.from_cache).count();
 
-        println!("\n📊 Batch Inspection Summary");
-        println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━");
+        println!("\n{}Batch Inspection Summary", plain::text("📊 "));
+        println!("{}", plain::text("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━"));
         println!("Total disks:      {}", results.len());
-        println!("✅ Successful:    {}", successful);
-        println!("❌ Failed:        {}", failed);
-        println!("💾 From cache:    {}", from_cache);
-        println!("⏱️  Total time:    {:?}", total_duration);
-        println!("⚡ Avg per disk:  {:?}", total_duration / results.len() as u32);
-        println!("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━\n");
+        println!("{} Successful:    {}", plain::text("✅"), successful);
+        println!("{} Failed:        {}", plain::text("❌"), failed);
+        println!("{}From cache:    {}", plain::text("💾 "), from_cache);
+        println!("{} Total time:    {:?}", plain::text("⏱️ "), total_duration);
+        println!("{}Avg per disk:  {:?}", plain::text("⚡ "), total_duration / results.len() as u32);
+        println!("{}\n", plain::text("━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━━"));
     }
 }
 
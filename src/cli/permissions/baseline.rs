@@ -0,0 +1,230 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Distro-default baselines for special permission bits
+
+use super::PermissionKind;
+use anyhow::Result;
+use serde::{Deserialize, Serialize};
+use std::fs;
+use std::path::Path;
+
+/// Set of paths expected to carry setuid/setgid/world-writable bits
+///
+/// Entries may be shell globs (e.g. `/usr/lib/*/utempter/utempter`).
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Baseline {
+    pub name: String,
+    #[serde(default)]
+    pub setuid: Vec<String>,
+    #[serde(default)]
+    pub setgid: Vec<String>,
+    #[serde(default)]
+    pub world_writable: Vec<String>,
+}
+
+const COMMON_SETUID: &[&str] = &[
+    "/usr/bin/su",
+    "/usr/bin/sudo",
+    "/usr/bin/passwd",
+    "/usr/bin/chsh",
+    "/usr/bin/chfn",
+    "/usr/bin/gpasswd",
+    "/usr/bin/newgrp",
+    "/usr/bin/newuidmap",
+    "/usr/bin/newgidmap",
+    "/usr/bin/mount",
+    "/usr/bin/umount",
+    "/usr/bin/pkexec",
+    "/usr/bin/fusermount",
+    "/usr/bin/fusermount3",
+    "/usr/bin/ping",
+    "/usr/lib/dbus-1.0/dbus-daemon-launch-helper",
+    "/usr/lib/polkit-1/polkit-agent-helper-1",
+];
+
+const COMMON_WORLD_WRITABLE: &[&str] = &[
+    "/tmp",
+    "/tmp/.X11-unix",
+    "/tmp/.ICE-unix",
+    "/tmp/.XIM-unix",
+    "/tmp/.font-unix",
+    "/tmp/.Test-unix",
+    "/var/tmp",
+    "/dev/shm",
+    "/dev/mqueue",
+    "/run/lock",
+    "/var/lock",
+    "/var/crash",
+    "/var/lib/php/sessions",
+];
+
+const DEBIAN_SETUID: &[&str] = &[
+    "/usr/lib/openssh/ssh-keysign",
+    "/usr/lib/eject/dmcrypt-get-device",
+    "/usr/lib/snapd/snap-confine",
+    "/usr/sbin/pppd",
+    "/usr/bin/ntfs-3g",
+    "/usr/bin/at",
+];
+
+const DEBIAN_SETGID: &[&str] = &[
+    "/usr/bin/crontab",
+    "/usr/bin/chage",
+    "/usr/bin/expiry",
+    "/usr/bin/ssh-agent",
+    "/usr/bin/wall",
+    "/usr/bin/write.ul",
+    "/usr/bin/bsd-write",
+    "/usr/bin/dotlockfile",
+    "/usr/bin/mlocate",
+    "/usr/bin/plocate",
+    "/usr/sbin/unix_chkpwd",
+    "/usr/sbin/pam_extrausers_chkpwd",
+    "/usr/lib/*/utempter/utempter",
+    "/usr/libexec/camel-lock-helper-*",
+];
+
+const REDHAT_SETUID: &[&str] = &[
+    "/usr/bin/at",
+    "/usr/bin/crontab",
+    "/usr/bin/staprun",
+    "/usr/sbin/pam_timestamp_check",
+    "/usr/sbin/unix_chkpwd",
+    "/usr/sbin/userhelper",
+    "/usr/sbin/usernetctl",
+    "/usr/sbin/mount.nfs",
+    "/usr/sbin/grub2-set-bootflag",
+    "/usr/libexec/dbus-1/dbus-daemon-launch-helper",
+    "/usr/libexec/polkit-1/polkit-agent-helper-1",
+    "/usr/libexec/sssd/krb5_child",
+    "/usr/libexec/sssd/ldap_child",
+    "/usr/libexec/sssd/proxy_child",
+    "/usr/libexec/sssd/selinux_child",
+];
+
+const REDHAT_SETGID: &[&str] = &[
+    "/usr/bin/write",
+    "/usr/bin/locate",
+    "/usr/libexec/utempter/utempter",
+    "/usr/libexec/openssh/ssh-keysign",
+];
+
+const SUSE_SETUID: &[&str] = &[
+    "/usr/bin/at",
+    "/usr/bin/crontab",
+    "/usr/sbin/unix_chkpwd",
+    "/usr/lib/polkit-1/polkit-agent-helper-1",
+    "/usr/lib/ssh/ssh-keysign",
+];
+
+const SUSE_SETGID: &[&str] = &["/usr/bin/write", "/usr/lib/utempter/utempter"];
+
+const ARCH_SETGID: &[&str] = &["/usr/lib/utempter/utempter"];
+
+const ALPINE_SETUID: &[&str] = &["/bin/bbsuid"];
+
+impl Baseline {
+    /// Built-in baseline for a distro as reported by `inspect_get_distro`
+    pub fn for_distro(distro: &str) -> Self {
+        let (family, setuid, setgid): (&str, &[&str], &[&str]) = match distro {
+            "debian" | "ubuntu" | "linuxmint" | "kalilinux" => {
+                ("debian", DEBIAN_SETUID, DEBIAN_SETGID)
+            }
+            "fedora" | "rhel" | "redhat-based" | "centos" | "rocky" | "almalinux"
+            | "oraclelinux" | "amazonlinux" => ("redhat", REDHAT_SETUID, REDHAT_SETGID),
+            "opensuse" | "sles" | "suse-based" => ("suse", SUSE_SETUID, SUSE_SETGID),
+            "archlinux" | "manjaro" => ("arch", &[], ARCH_SETGID),
+            "alpinelinux" | "alpine" => ("alpine", ALPINE_SETUID, &[]),
+            _ => ("generic", &[], &[]),
+        };
+
+        Self {
+            name: format!("{}-default", family),
+            setuid: COMMON_SETUID
+                .iter()
+                .chain(setuid)
+                .map(|s| s.to_string())
+                .collect(),
+            setgid: setgid.iter().map(|s| s.to_string()).collect(),
+            world_writable: COMMON_WORLD_WRITABLE.iter().map(|s| s.to_string()).collect(),
+        }
+    }
+
+    /// Load a baseline from a YAML (or JSON) file
+    pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let content = fs::read_to_string(path)?;
+        let baseline: Baseline = serde_yaml::from_str(&content)?;
+        Ok(baseline)
+    }
+
+    /// Extend this baseline with the entries of another
+    pub fn merge(&mut self, other: Baseline) {
+        if !other.name.is_empty() {
+            self.name = format!("{}+{}", self.name, other.name);
+        }
+        self.setuid.extend(other.setuid);
+        self.setgid.extend(other.setgid);
+        self.world_writable.extend(other.world_writable);
+    }
+
+    /// Whether `path` is expected to carry the given permission bit
+    ///
+    /// Paths under `/bin`, `/sbin` and `/lib` are also checked against their
+    /// merged-`/usr` location so one baseline covers both layouts.
+    pub fn allows(&self, kind: PermissionKind, path: &str) -> bool {
+        let patterns = match kind {
+            PermissionKind::Setuid => &self.setuid,
+            PermissionKind::Setgid => &self.setgid,
+            PermissionKind::WorldWritable => &self.world_writable,
+        };
+
+        let usr_merged = ["/bin/", "/sbin/", "/lib/", "/lib64/"]
+            .iter()
+            .find(|prefix| path.starts_with(*prefix))
+            .map(|_| format!("/usr{}", path));
+
+        std::iter::once(path)
+            .chain(usr_merged.as_deref())
+            .any(|candidate| patterns.iter().any(|p| matches_pattern(p, candidate)))
+    }
+}
+
+fn matches_pattern(pattern: &str, path: &str) -> bool {
+    if pattern.contains(['*', '?', '[']) {
+        glob::Pattern::new(pattern)
+            .map(|p| p.matches(path))
+            .unwrap_or(false)
+    } else {
+        pattern == path
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_distro_baseline() {
+        let baseline = Baseline::for_distro("ubuntu");
+        assert_eq!(baseline.name, "debian-default");
+        assert!(baseline.allows(PermissionKind::Setuid, "/usr/bin/sudo"));
+        assert!(baseline.allows(PermissionKind::Setuid, "/bin/su"));
+        assert!(baseline.allows(
+            PermissionKind::Setgid,
+            "/usr/lib/x86_64-linux-gnu/utempter/utempter"
+        ));
+        assert!(!baseline.allows(PermissionKind::Setuid, "/usr/local/bin/backdoor"));
+        assert!(!baseline.allows(PermissionKind::Setgid, "/usr/bin/sudo"));
+        assert!(baseline.allows(PermissionKind::WorldWritable, "/tmp"));
+    }
+
+    #[test]
+    fn test_merge_custom_baseline() {
+        let mut baseline = Baseline::for_distro("fedora");
+        let custom: Baseline =
+            serde_yaml::from_str("name: site\nsetuid:\n  - /opt/app/bin/helper\n").unwrap();
+        baseline.merge(custom);
+        assert_eq!(baseline.name, "redhat-default+site");
+        assert!(baseline.allows(PermissionKind::Setuid, "/opt/app/bin/helper"));
+        assert!(baseline.allows(PermissionKind::Setuid, "/usr/bin/crontab"));
+    }
+}
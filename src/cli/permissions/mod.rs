@@ -0,0 +1,253 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Special permission (SUID/SGID/world-writable) inventory module
+
+pub mod baseline;
+pub mod reporter;
+
+use crate::cli::plain;
+use crate::cli::target;
+use anyhow::Result;
+use baseline::Baseline;
+use guestkit::guestfs::SpecialPermEntry;
+use guestkit::Guestfs;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+
+/// Pseudo filesystems that never hold meaningful on-disk permissions
+const SKIPPED_PREFIXES: &[&str] = &["/proc/", "/sys/", "/dev/"];
+
+/// Kind of special permission carried by a file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum PermissionKind {
+    Setuid,
+    Setgid,
+    WorldWritable,
+}
+
+impl PermissionKind {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Setuid => "setuid",
+            Self::Setgid => "setgid",
+            Self::WorldWritable => "world-writable",
+        }
+    }
+}
+
+/// Classification of a file against the baseline
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EntryStatus {
+    /// Matches the distro-default (or custom) baseline
+    Expected,
+    /// Owned by a package but not part of the baseline
+    Anomaly,
+    /// Not owned by any installed package
+    Orphaned,
+}
+
+impl EntryStatus {
+    pub fn emoji(&self) -> &str {
+        match self {
+            Self::Expected => "✅",
+            Self::Anomaly => "⚠️",
+            Self::Orphaned => "🔴",
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Expected => "expected",
+            Self::Anomaly => "anomaly",
+            Self::Orphaned => "orphaned",
+        }
+    }
+}
+
+/// A file or directory with special permission bits
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionEntry {
+    pub path: String,
+    pub kinds: Vec<PermissionKind>,
+    pub mode: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub is_directory: bool,
+    pub sticky: bool,
+    pub package: Option<String>,
+    pub status: EntryStatus,
+    pub reason: String,
+}
+
+/// Permission inventory summary
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PermissionSummary {
+    pub setuid: usize,
+    pub setgid: usize,
+    pub world_writable: usize,
+    pub expected: usize,
+    pub anomalies: usize,
+    pub orphaned: usize,
+}
+
+/// Special permission inventory report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PermissionsReport {
+    pub image_path: String,
+    pub scanned_at: String,
+    pub distro: String,
+    pub baseline: String,
+    pub entries: Vec<PermissionEntry>,
+    pub summary: PermissionSummary,
+}
+
+impl PermissionsReport {
+    /// Entries that deviate from the baseline (anomalies and orphans)
+    pub fn findings(&self) -> impl Iterator<Item = &PermissionEntry> {
+        self.entries
+            .iter()
+            .filter(|e| e.status != EntryStatus::Expected)
+    }
+}
+
+/// Scan disk image for setuid/setgid and world-writable files
+pub fn scan_permissions<P: AsRef<Path>>(
+    image_path: P,
+    baseline_file: Option<&Path>,
+    verbose: bool,
+) -> Result<PermissionsReport> {
+    let image_path_str = image_path.as_ref().display().to_string();
+
+    if verbose {
+        println!("{}Scanning special permissions in: {}", plain::text("🔐 "), image_path_str);
+    }
+
+    // Initialize guestfs
+    let mut g = Guestfs::new()?;
+    g.add_drive_opts(&image_path, true, None)?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
+    g.launch()?;
+
+    // Inspect OS
+    let roots = g.inspect_os()?;
+    if roots.is_empty() {
+        anyhow::bail!("No operating systems found in disk image");
+    }
+
+    let root = &roots[0];
+    let distro = g.inspect_get_distro(root).unwrap_or_else(|_| "unknown".to_string());
+
+    // Mount filesystems
+    g.mount_os_ro(root)?;
+
+    let mut baseline = Baseline::for_distro(&distro);
+    if let Some(path) = baseline_file {
+        baseline.merge(Baseline::from_file(path)?);
+    }
+
+    let found = g.find_special_perms("/")?;
+    if verbose {
+        println!("  Found {} files with special permissions", found.len());
+    }
+
+    let owners = g.package_file_owners()?;
+    if verbose {
+        println!("  Indexed {} packaged paths", owners.len());
+    }
+
+    let entries: Vec<PermissionEntry> = found
+        .iter()
+        .filter(|e| !SKIPPED_PREFIXES.iter().any(|p| e.path.starts_with(p)))
+        .filter_map(|e| classify(e, owners.get(&e.path).cloned(), &baseline))
+        .collect();
+
+    // Shutdown guestfs
+    g.shutdown()?;
+
+    let summary = calculate_summary(&entries);
+
+    Ok(PermissionsReport {
+        image_path: image_path_str,
+        scanned_at: chrono::Utc::now().to_rfc3339(),
+        distro,
+        baseline: baseline.name,
+        entries,
+        summary,
+    })
+}
+
+fn classify(
+    entry: &SpecialPermEntry,
+    package: Option<String>,
+    baseline: &Baseline,
+) -> Option<PermissionEntry> {
+    let mut kinds = Vec::new();
+    if entry.is_setuid() {
+        kinds.push(PermissionKind::Setuid);
+    }
+    // Setgid on directories only controls group inheritance
+    if entry.is_setgid() && !entry.is_dir() {
+        kinds.push(PermissionKind::Setgid);
+    }
+    if entry.is_world_writable() {
+        kinds.push(PermissionKind::WorldWritable);
+    }
+    if kinds.is_empty() {
+        return None;
+    }
+
+    let unexpected: Vec<PermissionKind> = kinds
+        .iter()
+        .copied()
+        .filter(|kind| {
+            // A world-writable directory is only safe with the sticky bit set
+            let sticky_ok = *kind != PermissionKind::WorldWritable
+                || !entry.is_dir()
+                || entry.is_sticky();
+            !(sticky_ok && baseline.allows(*kind, &entry.path))
+        })
+        .collect();
+
+    let (status, reason) = if unexpected.is_empty() {
+        (EntryStatus::Expected, format!("listed in {} baseline", baseline.name))
+    } else if package.is_none() {
+        (EntryStatus::Orphaned, "not owned by any installed package".to_string())
+    } else if entry.is_world_writable() && entry.is_dir() && !entry.is_sticky() {
+        (EntryStatus::Anomaly, "world-writable directory without sticky bit".to_string())
+    } else {
+        let bits: Vec<&str> = unexpected.iter().map(|k| k.as_str()).collect();
+        (EntryStatus::Anomaly, format!("{} not in {} baseline", bits.join("+"), baseline.name))
+    };
+
+    Some(PermissionEntry {
+        path: entry.path.clone(),
+        kinds,
+        mode: format!("{:04o}", entry.mode & 0o7777),
+        uid: entry.uid,
+        gid: entry.gid,
+        is_directory: entry.is_dir(),
+        sticky: entry.is_sticky(),
+        package,
+        status,
+        reason,
+    })
+}
+
+fn calculate_summary(entries: &[PermissionEntry]) -> PermissionSummary {
+    let mut summary = PermissionSummary::default();
+    for entry in entries {
+        for kind in &entry.kinds {
+            match kind {
+                PermissionKind::Setuid => summary.setuid += 1,
+                PermissionKind::Setgid => summary.setgid += 1,
+                PermissionKind::WorldWritable => summary.world_writable += 1,
+            }
+        }
+        match entry.status {
+            EntryStatus::Expected => summary.expected += 1,
+            EntryStatus::Anomaly => summary.anomalies += 1,
+            EntryStatus::Orphaned => summary.orphaned += 1,
+        }
+    }
+    summary
+}
@@ -0,0 +1,102 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Permission inventory report formatting
+
+use super::{EntryStatus, PermissionsReport};
+
+/// Format permission report as text
+pub fn format_report(report: &PermissionsReport, show_all: bool) -> String {
+    let mut output = String::new();
+
+    output.push_str("🔐 Special Permissions Report\n");
+    output.push_str("=============================\n\n");
+    output.push_str(&format!("Image: {}\n", report.image_path));
+    output.push_str(&format!("Scanned: {}\n", report.scanned_at));
+    output.push_str(&format!("Distribution: {}\n", report.distro));
+    output.push_str(&format!("Baseline: {}\n\n", report.baseline));
+
+    // Summary
+    output.push_str("📊 Summary\n");
+    output.push_str("----------\n");
+    output.push_str(&format!("Setuid: {}\n", report.summary.setuid));
+    output.push_str(&format!("Setgid: {}\n", report.summary.setgid));
+    output.push_str(&format!("World-writable: {}\n", report.summary.world_writable));
+    output.push_str(&format!(
+        "{} Expected: {}\n",
+        EntryStatus::Expected.emoji(),
+        report.summary.expected
+    ));
+    output.push_str(&format!(
+        "{}  Anomalies: {}\n",
+        EntryStatus::Anomaly.emoji(),
+        report.summary.anomalies
+    ));
+    output.push_str(&format!(
+        "{} Orphaned: {}\n\n",
+        EntryStatus::Orphaned.emoji(),
+        report.summary.orphaned
+    ));
+
+    let entries: Vec<_> = if show_all {
+        report.entries.iter().collect()
+    } else {
+        report.findings().collect()
+    };
+
+    if !entries.is_empty() {
+        output.push_str(if show_all { "📁 All Entries\n" } else { "🚨 Findings\n" });
+        output.push_str("------------\n");
+        for entry in entries {
+            let kinds: Vec<&str> = entry.kinds.iter().map(|k| k.as_str()).collect();
+            output.push_str(&format!(
+                "{} {} {} [{}] uid={} gid={}\n",
+                entry.status.emoji(),
+                entry.mode,
+                entry.path,
+                kinds.join(","),
+                entry.uid,
+                entry.gid
+            ));
+            output.push_str(&format!(
+                "   📦 {} - {}\n",
+                entry.package.as_deref().unwrap_or("(no package)"),
+                entry.reason
+            ));
+        }
+        output.push('\n');
+    }
+
+    // Overall assessment
+    let findings = report.summary.anomalies + report.summary.orphaned;
+    if findings == 0 {
+        output.push_str("✅ All special permissions match the baseline\n");
+    } else {
+        output.push_str(&format!(
+            "❌ Found {} entries deviating from the baseline - review required\n",
+            findings
+        ));
+    }
+
+    output
+}
+
+/// Format permission report as CSV
+pub fn format_csv(report: &PermissionsReport) -> String {
+    let mut output = String::from("Path,Mode,Kinds,UID,GID,Package,Status,Reason\n");
+
+    for entry in &report.entries {
+        let kinds: Vec<&str> = entry.kinds.iter().map(|k| k.as_str()).collect();
+        output.push_str(&format!(
+            "\"{}\",{},{},{},{},{},{},\"{}\"\n",
+            entry.path.replace('"', "\"\""),
+            entry.mode,
+            kinds.join("+"),
+            entry.uid,
+            entry.gid,
+            entry.package.as_deref().unwrap_or(""),
+            entry.status.as_str(),
+            entry.reason.replace('"', "\"\"")
+        ));
+    }
+
+    output
+}
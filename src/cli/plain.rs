@@ -0,0 +1,182 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Plain presentation mode (`--plain`)
+//!
+//! Reports use emoji severity markers and box-drawing characters, which
+//! screen readers announce badly and legacy terminals render as garbage.
+//! Plain mode replaces them with short text markers (`[OK]`, `[WARN]`,
+//! `[FAIL]`) and ASCII table borders.
+//!
+//! The substitution happens only on the way to the terminal, so exported
+//! files and `--output` reports keep their markers. Commands pass the
+//! markers and headings they print through [`text`], and guest data, JSON
+//! or YAML around them unchanged. Text reports rendered before printing
+//! are converted as a whole by [`report`].
+
+use std::borrow::Cow;
+use std::sync::atomic::{AtomicBool, Ordering};
+
+static ENABLED: AtomicBool = AtomicBool::new(false);
+
+/// Turn plain mode on for the rest of the process
+pub fn enable() {
+    ENABLED.store(true, Ordering::Relaxed);
+}
+
+/// Whether plain mode is on
+pub fn enabled() -> bool {
+    ENABLED.load(Ordering::Relaxed)
+}
+
+/// Environment variable set while plain mode is active
+pub const PLAIN_ENV: &str = "GUESTCTL_PLAIN";
+
+/// Replacements for multi-character sequences and markers with meaning
+///
+/// Checked in order, so longer sequences come before their prefixes.
+const SYMBOLS: &[(&str, &str)] = &[
+    ("⚠️", "[WARN]"),
+    ("⚠", "[WARN]"),
+    ("ℹ️", "[INFO]"),
+    ("ℹ", "[INFO]"),
+    ("✅", "[OK]"),
+    ("✓", "[OK]"),
+    ("✔", "[OK]"),
+    ("❌", "[FAIL]"),
+    ("✗", "[FAIL]"),
+    ("✘", "[FAIL]"),
+    ("⛔", "[FAIL]"),
+    ("🔴", "[CRITICAL]"),
+    ("🟠", "[HIGH]"),
+    ("🟡", "[MEDIUM]"),
+    ("🟢", "[LOW]"),
+    ("🔵", "[INFO]"),
+    ("└─", "`-"),
+    ("├─", "|-"),
+    ("→", "->"),
+    ("←", "<-"),
+    ("⇒", "=>"),
+    ("↑", "^"),
+    ("↓", "v"),
+    ("…", "..."),
+    ("•", "*"),
+];
+
+/// Emoji and pictographs without a textual equivalent; dropped in plain mode
+fn is_pictograph(c: char) -> bool {
+    matches!(c,
+        '\u{1F000}'..='\u{1FAFF}'
+        | '\u{2600}'..='\u{27BF}'
+        | '\u{2B00}'..='\u{2BFF}'
+        | '\u{2190}'..='\u{21FF}'
+        | '\u{2300}'..='\u{23FF}')
+}
+
+/// ASCII stand-in for a box-drawing character (U+2500-U+257F)
+fn box_char(c: char) -> char {
+    match c {
+        '═' | '━' => '=',
+        '─' | '┄' | '┅' | '┈' | '┉' | '╌' | '╍' | '╴' | '╶' | '╸' | '╺' => {
+            '-'
+        }
+        '│' | '┃' | '║' | '┆' | '┇' | '┊' | '┋' | '╎' | '╏' | '╵' | '╷' | '╹' | '╻' => {
+            '|'
+        }
+        _ => '+',
+    }
+}
+
+/// Convert decorated text to plain ASCII markers
+///
+/// Every emoji, arrow and box-drawing character in `text` is replaced, so
+/// only pass text written by guestctl, never guest data.
+pub fn to_plain(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut rest = text;
+
+    'next: while let Some(c) = rest.chars().next() {
+        for (symbol, replacement) in SYMBOLS {
+            if let Some(tail) = rest.strip_prefix(symbol) {
+                out.push_str(replacement);
+                rest = tail;
+                continue 'next;
+            }
+        }
+        rest = &rest[c.len_utf8()..];
+
+        match c {
+            '\u{2500}'..='\u{257F}' => out.push(box_char(c)),
+            '░' | '▒' | '▓' => out.push('.'),
+            '\u{2580}'..='\u{259F}' => out.push('#'),
+            '\u{25A0}'..='\u{25FF}' => out.push('*'),
+            // Variation selectors and joiners only modify emoji
+            '\u{FE0E}' | '\u{FE0F}' | '\u{200D}' => {}
+            c if is_pictograph(c) => {
+                // "📦 Packages" reads as "Packages", not " Packages"
+                if out.is_empty() || out.ends_with(char::is_whitespace) {
+                    rest = rest.strip_prefix(' ').unwrap_or(rest);
+                }
+            }
+            c => out.push(c),
+        }
+    }
+
+    out
+}
+
+/// Text written by guestctl for the terminal, converted when plain mode is on
+///
+/// For status markers, rules and headings printed next to guest data, such
+/// as `println!("{} {}", plain::text("✓"), name)`.
+pub fn text(text: &str) -> Cow<'_, str> {
+    if enabled() {
+        Cow::Owned(to_plain(text))
+    } else {
+        Cow::Borrowed(text)
+    }
+}
+
+/// Rendered text report, converted when plain mode is on and it is printed
+///
+/// Reports interpolate guest data into their text, so in plain mode an
+/// arrow or emoji in a printed file name is converted as well. A report
+/// written to a file (`printed` false) is left as rendered.
+pub fn report(text: String, printed: bool) -> String {
+    if printed && enabled() {
+        to_plain(&text)
+    } else {
+        text
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_severity_markers() {
+        assert_eq!(to_plain("✅ Host is ready"), "[OK] Host is ready");
+        assert_eq!(to_plain("⚠️ /dev/kvm: missing"), "[WARN] /dev/kvm: missing");
+        assert_eq!(to_plain("  ✗ sshd: failed"), "  [FAIL] sshd: failed");
+        assert_eq!(to_plain("🔴 Critical: 2"), "[CRITICAL] Critical: 2");
+    }
+
+    #[test]
+    fn test_decorations_removed() {
+        assert_eq!(to_plain("🩺 Host Prerequisites"), "Host Prerequisites");
+        assert_eq!(
+            to_plain("   └─ fix: modprobe nbd"),
+            "   `- fix: modprobe nbd"
+        );
+        assert_eq!(to_plain("━━━ Summary ━━━"), "=== Summary ===");
+        assert_eq!(to_plain("┌──┬──┐\n│a │b │"), "+--+--+\n|a |b |");
+        assert_eq!(to_plain("1.0 → 2.0"), "1.0 -> 2.0");
+    }
+
+    #[test]
+    fn test_non_ascii_text_preserved() {
+        assert_eq!(
+            to_plain("hostname: überserver-東京"),
+            "hostname: überserver-東京"
+        );
+    }
+}
@@ -2,7 +2,10 @@
 //! Plan command - manage fix plans
 
 use super::*;
+use crate::cli::audit_log::audited_with;
+use crate::cli::plain;
 use anyhow::{Context, Result};
+use guestkit::core::AuditResult;
 use clap::{Args, Subcommand};
 use colored::*;
 use std::fs;
@@ -211,28 +214,28 @@ impl PlanCommand {
         let result = applicator.validate(&plan)?;
 
         if result.valid {
-            println!("{}", "✓ Plan is valid".green().bold());
+            println!("{}", plain::text("✓ Plan is valid").green().bold());
 
             if !result.warnings.is_empty() {
                 println!();
                 println!("{}", "Warnings:".yellow().bold());
                 for warning in &result.warnings {
-                    println!("  ⚠️  {}", warning.yellow());
+                    println!("  {}  {}", plain::text("⚠️"), warning.yellow());
                 }
             }
         } else {
-            println!("{}", "✗ Plan validation failed".red().bold());
+            println!("{}", plain::text("✗ Plan validation failed").red().bold());
             println!();
             println!("{}", "Errors:".red().bold());
             for error in &result.errors {
-                println!("  ✗ {}", error.red());
+                println!("  {} {}", plain::text("✗"), error.red());
             }
 
             if !result.warnings.is_empty() {
                 println!();
                 println!("{}", "Warnings:".yellow().bold());
                 for warning in &result.warnings {
-                    println!("  ⚠️  {}", warning.yellow());
+                    println!("  {}  {}", plain::text("⚠️"), warning.yellow());
                 }
             }
 
@@ -264,7 +267,7 @@ impl PlanCommand {
         fs::write(output, content)
             .with_context(|| format!("Failed to write output file: {}", output))?;
 
-        println!("{} Exported to: {}", "✓".green(), output.bright_blue());
+        println!("{} Exported to: {}", plain::text("✓").green(), output.bright_blue());
 
         Ok(())
     }
@@ -276,7 +279,7 @@ impl PlanCommand {
         dry_run: bool,
         yes: bool,
         interactive: bool,
-        _backup_dir: Option<&str>,
+        backup_dir: Option<&str>,
     ) -> Result<()> {
         let plan = self.load_plan(plan_file)?;
         let vm_path = vm_override.unwrap_or(&plan.vm);
@@ -286,9 +289,9 @@ impl PlanCommand {
         let validation = applicator.validate(&plan)?;
 
         if !validation.valid {
-            println!("{}", "✗ Plan validation failed".red().bold());
+            println!("{}", plain::text("✗ Plan validation failed").red().bold());
             for error in &validation.errors {
-                println!("  ✗ {}", error.red());
+                println!("  {} {}", plain::text("✗"), error.red());
             }
             anyhow::bail!("Cannot apply invalid plan");
         }
@@ -314,7 +317,10 @@ impl PlanCommand {
         }
 
         // Apply
-        let applicator = PlanApplicator::new(vm_path.to_string(), dry_run);
+        let mut applicator = PlanApplicator::new(vm_path.to_string(), dry_run);
+        if let Some(dir) = backup_dir {
+            applicator = applicator.with_backup_dir(dir);
+        }
 
         if dry_run {
             println!();
@@ -322,20 +328,56 @@ impl PlanCommand {
             println!();
         }
 
-        let result = applicator.apply(&plan)?;
+        let result = if dry_run {
+            applicator.apply(&plan)?
+        } else {
+            let parameters = serde_json::json!({
+                "plan_file": plan_file,
+                "profile": plan.profile,
+                "operations": plan.operations.len(),
+                "backup_dir": backup_dir,
+            });
+            audited_with(
+                "plan.apply",
+                Path::new(vm_path),
+                parameters,
+                || applicator.apply(&plan),
+                |result| {
+                    let summary = format!(
+                        "{} applied, {} skipped, {} failed",
+                        result.operations_applied,
+                        result.operations_skipped,
+                        result.operations_failed
+                    );
+                    if result.success {
+                        AuditResult::success().with_message(summary)
+                    } else {
+                        AuditResult::failure(format!("{}: {}", result.message, summary))
+                    }
+                },
+            )?
+        };
 
         println!();
         if result.success {
-            println!("{}", "✓ Plan applied successfully".green().bold());
+            println!("{}", plain::text("✓ Plan applied successfully").green().bold());
             println!("  Operations applied: {}", result.operations_applied);
             println!("  Operations skipped: {}", result.operations_skipped);
         } else {
-            println!("{}", "✗ Plan application failed".red().bold());
+            println!("{}", plain::text("✗ Plan application failed").red().bold());
             println!("  Operations applied: {}", result.operations_applied);
             println!("  Operations failed: {}", result.operations_failed);
             println!("  Message: {}", result.message);
         }
 
+        for op in result.operations.iter().filter(|op| op.outcome != apply::OperationOutcome::Applied) {
+            println!("  {} {}: {}", op.id.bright_black(), op.description, op.message);
+        }
+
+        if let Some(ref rollback_file) = result.rollback_file {
+            println!("  Rollback manifest: {}", rollback_file.bright_blue());
+        }
+
         Ok(())
     }
 
@@ -345,10 +387,10 @@ impl PlanCommand {
         }
 
         println!("{}", "Rollback Operation".bold().red());
-        println!("{}", "═".repeat(60).bright_black());
+        println!("{}", plain::text("═").repeat(60).bright_black());
         println!("Backup: {}", backup_dir.bright_blue());
         println!("VM: {}", vm.bright_blue());
-        println!("{}", "═".repeat(60).bright_black());
+        println!("{}", plain::text("═").repeat(60).bright_black());
         println!();
         println!("{}", "WARNING: This will restore files from backup.".yellow().bold());
         println!("{}", "Any changes made after the backup will be lost.".yellow());
@@ -372,7 +414,7 @@ impl PlanCommand {
         applicator.rollback(backup_dir)?;
 
         println!();
-        println!("{}", "✓ Rollback completed successfully".green().bold());
+        println!("{}", plain::text("✓ Rollback completed successfully").green().bold());
 
         Ok(())
     }
@@ -399,7 +441,7 @@ impl PlanCommand {
 
         println!();
         println!("{}", "Plan Statistics".bold().cyan());
-        println!("{}", "═".repeat(60).bright_black());
+        println!("{}", plain::text("═").repeat(60).bright_black());
         println!();
         println!("File: {}", plan_file.bright_blue());
         println!("VM: {}", plan.vm);
@@ -10,10 +10,10 @@
 
 #![allow(unused_imports)]
 
-pub mod types;
+pub use guestkit::plan::{apply, types};
+
 pub mod generator;
 pub mod preview;
-pub mod apply;
 pub mod export;
 pub mod command;
 
@@ -2,6 +2,7 @@
 //! Plan preview and diff display
 
 use super::types::*;
+use crate::cli::plain;
 use colored::*;
 
 /// Displays fix plans in human-readable format
@@ -18,7 +19,7 @@ impl PlanPreview {
     /// Display a plan as unified diff
     pub fn display_diff(plan: &FixPlan) {
         println!("{}", "Diff Preview".bold().cyan());
-        println!("{}", "═".repeat(60).bright_black());
+        println!("{}", plain::text("═").repeat(60).bright_black());
         println!();
 
         for op in &plan.operations {
@@ -29,8 +30,8 @@ impl PlanPreview {
     /// Print plan header
     fn print_header(plan: &FixPlan) {
         println!();
-        println!("{}", "📋 Fix Plan Preview".bold().cyan());
-        println!("{}", "═".repeat(60).bright_black());
+        println!("{}", plain::text("📋 Fix Plan Preview").bold().cyan());
+        println!("{}", plain::text("═").repeat(60).bright_black());
         println!();
         println!("{}: {}", "VM".bold(), plan.vm);
         println!("{}: {} ({} risk)",
@@ -41,7 +42,7 @@ impl PlanPreview {
         println!("{}: {}", "Operations".bold(), plan.operations.len());
         println!("{}: {}", "Estimated Duration".bold(), plan.estimated_duration);
         println!();
-        println!("{}", "━".repeat(60).bright_black());
+        println!("{}", plain::text("━").repeat(60).bright_black());
         println!();
     }
 
@@ -57,7 +58,7 @@ impl PlanPreview {
             }
 
             println!("{} {} Priority ({} operations)",
-                priority.emoji(),
+                plain::text(priority.emoji()),
                 priority.as_str().to_uppercase(),
                 ops.len()
             );
@@ -78,9 +79,11 @@ impl PlanPreview {
                 println!("  File: {}", fe.file.bright_blue());
                 for change in &fe.changes {
                     if change.line > 0 {
-                        println!("  Line {}: {} → {}",
+                        println!(
+                            "  Line {}: {} {} {}",
                             change.line,
                             change.before.red(),
+                            plain::text("→"),
                             change.after.green()
                         );
                     }
@@ -103,19 +106,18 @@ impl PlanPreview {
             }
             OperationType::SelinuxMode(sm) => {
                 println!("  File: {}", sm.file.bright_blue());
-                println!("  {} → {}",
-                    sm.current.red(),
-                    sm.target.green()
-                );
+                println!("  {} {} {}", sm.current.red(), plain::text("→"), sm.target.green());
                 if let Some(warning) = &sm.warning {
-                    println!("  ⚠️  {}", warning.yellow());
+                    println!("  {}  {}", plain::text("⚠️"), warning.yellow());
                 }
             }
             OperationType::RegistryEdit(re) => {
                 println!("  Key: {}", re.key.bright_blue());
                 println!("  Value: {}", re.value);
-                println!("  {} → {}",
+                println!(
+                    "  {} {} {}",
                     re.current_data.to_string().red(),
+                    plain::text("→"),
                     re.new_data.to_string().green()
                 );
             }
@@ -123,8 +125,10 @@ impl PlanPreview {
                 println!("  Command: {}", ce.command.bright_cyan());
             }
             OperationType::FileCopy(fc) => {
-                println!("  {} → {}",
+                println!(
+                    "  {} {} {}",
                     fc.source.bright_blue(),
+                    plain::text("→"),
                     fc.destination.bright_green()
                 );
             }
@@ -174,7 +178,7 @@ impl PlanPreview {
 
     /// Print plan footer
     fn print_footer(plan: &FixPlan) {
-        println!("{}", "━".repeat(60).bright_black());
+        println!("{}", plain::text("━").repeat(60).bright_black());
         println!();
 
         if !plan.operations.is_empty() {
@@ -184,8 +188,10 @@ impl PlanPreview {
                 println!("{}", "Dependencies:".bold());
                 for op in &plan.operations {
                     if !op.depends_on.is_empty() {
-                        println!("  {} → {}",
+                        println!(
+                            "  {} {} {}",
                             op.depends_on.join(", ").yellow(),
+                            plain::text("→"),
                             op.id.yellow()
                         );
                     }
@@ -199,16 +205,20 @@ impl PlanPreview {
                 for action in &plan.post_apply {
                     match action {
                         PostApplyAction::ServiceRestart { services } => {
-                            println!("  • Restart services: {}", services.join(", ").bright_cyan());
+                            println!(
+                                "  {} Restart services: {}",
+                                plain::text("•"),
+                                services.join(", ").bright_cyan()
+                            );
                         }
                         PostApplyAction::Validation { command, .. } => {
-                            println!("  • Validate: {}", command.bright_blue());
+                            println!("  {} Validate: {}", plain::text("•"), command.bright_blue());
                         }
                         PostApplyAction::Message { message } => {
-                            println!("  • {}", message);
+                            println!("  {} {}", plain::text("•"), message);
                         }
                         PostApplyAction::RebootRequired { reason } => {
-                            println!("  {} Reboot required: {}", "⚠️".yellow(), reason.yellow());
+                            println!("  {} Reboot required: {}", plain::text("⚠️").yellow(), reason.yellow());
                         }
                     }
                 }
@@ -235,7 +245,7 @@ impl PlanPreview {
     /// Print summary statistics
     pub fn print_summary(plan: &FixPlan) {
         println!("{}", "Plan Summary".bold().cyan());
-        println!("{}", "─".repeat(40));
+        println!("{}", plain::text("─").repeat(40));
         println!("Total Operations: {}", plan.operations.len());
         println!("Critical: {}", plan.count_by_priority(Priority::Critical));
         println!("High: {}", plan.count_by_priority(Priority::High));
@@ -0,0 +1,291 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! External command plugins
+//!
+//! An executable named `guestctl-NAME` in a PATH directory runs as
+//! `guestctl NAME ARGS...`; built-in commands take precedence. The plugin
+//! gets its arguments unchanged and a JSON [`PluginContext`] in
+//! `GUESTCTL_CONTEXT`: the guestctl binary to call back, the global flags,
+//! and the image its first argument names, resolved like the image
+//! arguments of built-in commands (catalog names, `oci://` references).
+
+use crate::cli::catalog::parse_image_ref;
+use anyhow::{bail, Context, Result};
+use colored::Colorize;
+use serde::Serialize;
+use std::ffi::OsString;
+use std::os::unix::fs::PermissionsExt;
+use std::os::unix::process::ExitStatusExt;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// File name prefix of plugin executables
+pub const PLUGIN_PREFIX: &str = "guestctl-";
+
+/// Environment variable holding the JSON context of a plugin
+pub const CONTEXT_ENV: &str = "GUESTCTL_CONTEXT";
+
+/// Version of the context layout, raised on incompatible changes
+pub const CONTEXT_VERSION: u32 = 1;
+
+/// An external command found on PATH
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Plugin {
+    /// Command name, the executable name without `guestctl-`
+    pub name: String,
+    pub path: PathBuf,
+}
+
+/// Global flags of the guestctl invocation
+///
+/// Encryption keys given with `--key` are not passed on.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct GlobalFlags {
+    pub verbose: bool,
+    pub debug: bool,
+    pub quiet: bool,
+    pub no_color: bool,
+    pub read_only: bool,
+    /// Seconds, 0 for none
+    pub timeout: u64,
+    pub cache_dir: Option<PathBuf>,
+    pub jobs: Option<usize>,
+    pub machine_readable: bool,
+    pub plain: bool,
+    pub lang: Option<String>,
+    pub os_root: Option<String>,
+    pub extra_disks: Vec<PathBuf>,
+    pub mount_options: Option<String>,
+}
+
+/// What a plugin is told about its invocation
+#[derive(Debug, Clone, Serialize)]
+pub struct PluginContext {
+    pub context_version: u32,
+    pub guestctl_version: String,
+    /// The guestctl binary, for plugins that run built-in commands
+    pub guestctl: Option<PathBuf>,
+    pub plugin: String,
+    pub arguments: Vec<String>,
+    pub global: GlobalFlags,
+    /// Disk image the first argument names, if it names one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub image: Option<PathBuf>,
+}
+
+impl PluginContext {
+    pub fn new(plugin: &Plugin, arguments: &[String], global: GlobalFlags) -> Self {
+        Self {
+            context_version: CONTEXT_VERSION,
+            guestctl_version: guestkit::VERSION.to_string(),
+            guestctl: std::env::current_exe().ok(),
+            plugin: plugin.name.clone(),
+            arguments: arguments.to_vec(),
+            global,
+            image: image_argument(arguments),
+        }
+    }
+}
+
+/// Image named by the first positional argument
+fn image_argument(arguments: &[String]) -> Option<PathBuf> {
+    let first = arguments
+        .iter()
+        .find(|argument| !argument.starts_with('-'))?;
+    parse_image_ref(first).ok().filter(|path| path.exists())
+}
+
+/// Plugins in the PATH directories, by name
+///
+/// When several directories have a plugin of the same name, the first one
+/// is used, as the shell would.
+pub fn discover() -> Vec<Plugin> {
+    let path = std::env::var_os("PATH").unwrap_or_default();
+    discover_in(std::env::split_paths(&path))
+}
+
+fn discover_in(dirs: impl IntoIterator<Item = PathBuf>) -> Vec<Plugin> {
+    let mut plugins: Vec<Plugin> = Vec::new();
+    for dir in dirs {
+        let Ok(entries) = std::fs::read_dir(&dir) else {
+            continue;
+        };
+        let mut found: Vec<Plugin> = entries
+            .filter_map(|entry| entry.ok())
+            .filter_map(|entry| {
+                let file_name = entry.file_name().into_string().ok()?;
+                let name = file_name.strip_prefix(PLUGIN_PREFIX)?;
+                let path = entry.path();
+                (!name.is_empty() && is_executable(&path)).then(|| Plugin {
+                    name: name.to_string(),
+                    path,
+                })
+            })
+            .filter(|plugin| plugins.iter().all(|p| p.name != plugin.name))
+            .collect();
+        found.sort_by(|a, b| a.name.cmp(&b.name));
+        plugins.extend(found);
+    }
+    plugins.sort_by(|a, b| a.name.cmp(&b.name));
+    plugins
+}
+
+fn is_executable(path: &Path) -> bool {
+    std::fs::metadata(path)
+        .map(|metadata| metadata.is_file() && metadata.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// Run the plugin for `guestctl NAME ARGS...`; `command` is NAME followed
+/// by the arguments
+///
+/// Returns the exit code of the plugin.
+pub fn run_plugin(command: &[OsString], global: GlobalFlags) -> Result<i32> {
+    let Some((name, arguments)) = command.split_first() else {
+        bail!("No command given");
+    };
+    let name = name.to_string_lossy();
+    let Some(plugin) = discover().into_iter().find(|plugin| plugin.name == name) else {
+        bail!(
+            "Unknown command '{}': not a built-in command, and no {}{} on PATH \
+             (see 'guestctl --help' and 'guestctl plugins')",
+            name,
+            PLUGIN_PREFIX,
+            name
+        );
+    };
+
+    let arguments: Vec<String> = arguments
+        .iter()
+        .map(|argument| argument.to_string_lossy().into_owned())
+        .collect();
+    let context = PluginContext::new(&plugin, &arguments, global);
+    log::debug!("Running plugin {}", plugin.path.display());
+
+    let status = Command::new(&plugin.path)
+        .args(&command[1..])
+        .env(CONTEXT_ENV, serde_json::to_string(&context)?)
+        .status()
+        .with_context(|| format!("Failed to execute {}", plugin.path.display()))?;
+    // A plugin killed by a signal exits like a shell reports it
+    Ok(status
+        .code()
+        .or_else(|| status.signal().map(|signal| 128 + signal))
+        .unwrap_or(1))
+}
+
+/// List the plugins on PATH; `builtins` are the names of built-in commands,
+/// which hide plugins of the same name
+pub fn list_command(builtins: &[String], format: &str) -> Result<()> {
+    let plugins = discover();
+    let hidden = |plugin: &Plugin| builtins.contains(&plugin.name);
+
+    if format == "json" {
+        let plugins: Vec<serde_json::Value> = plugins
+            .iter()
+            .map(|plugin| {
+                serde_json::json!({
+                    "name": plugin.name,
+                    "path": plugin.path,
+                    "hidden_by_builtin": hidden(plugin),
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&plugins)?);
+        return Ok(());
+    }
+
+    if plugins.is_empty() {
+        println!(
+            "No plugins found. Executables named {}NAME on PATH run as 'guestctl NAME'.",
+            PLUGIN_PREFIX
+        );
+        return Ok(());
+    }
+    for plugin in &plugins {
+        if hidden(plugin) {
+            println!(
+                "  {:<20} {} {}",
+                plugin.name,
+                plugin.path.display(),
+                "(hidden by the built-in command)".yellow()
+            );
+        } else {
+            println!("  {:<20} {}", plugin.name.bold(), plugin.path.display());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn script(dir: &Path, name: &str, mode: u32) {
+        let path = dir.join(name);
+        std::fs::write(&path, "#!/bin/sh\necho \"$GUESTCTL_CONTEXT\"\n").unwrap();
+        std::fs::set_permissions(&path, std::fs::Permissions::from_mode(mode)).unwrap();
+    }
+
+    #[test]
+    fn test_discover() {
+        let first = TempDir::new().unwrap();
+        let second = TempDir::new().unwrap();
+        script(first.path(), "guestctl-report", 0o755);
+        script(first.path(), "guestctl-notes", 0o644);
+        script(first.path(), "guestctl-", 0o755);
+        script(first.path(), "other-tool", 0o755);
+        script(second.path(), "guestctl-report", 0o755);
+        script(second.path(), "guestctl-backup", 0o755);
+
+        let plugins = discover_in([
+            first.path().to_path_buf(),
+            PathBuf::from("/nonexistent"),
+            second.path().to_path_buf(),
+        ]);
+        assert_eq!(
+            plugins,
+            vec![
+                Plugin {
+                    name: "backup".to_string(),
+                    path: second.path().join("guestctl-backup"),
+                },
+                Plugin {
+                    name: "report".to_string(),
+                    path: first.path().join("guestctl-report"),
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_context() {
+        let dir = TempDir::new().unwrap();
+        let image = dir.path().join("web.qcow2");
+        std::fs::write(&image, b"").unwrap();
+        let plugin = Plugin {
+            name: "report".to_string(),
+            path: PathBuf::from("/usr/local/bin/guestctl-report"),
+        };
+        let global = GlobalFlags {
+            read_only: true,
+            ..Default::default()
+        };
+
+        let arguments = vec!["--html".to_string(), image.display().to_string()];
+        let context = PluginContext::new(&plugin, &arguments, global.clone());
+        assert_eq!(context.image, Some(image));
+        let json = serde_json::to_value(&context).unwrap();
+        assert_eq!(json["context_version"], 1);
+        assert_eq!(json["plugin"], "report");
+        assert_eq!(json["global"]["read_only"], true);
+
+        let arguments = vec!["no-such-image.qcow2".to_string()];
+        let context = PluginContext::new(&plugin, &arguments, global);
+        assert_eq!(context.image, None);
+        assert!(serde_json::to_value(&context)
+            .unwrap()
+            .get("image")
+            .is_none());
+    }
+}
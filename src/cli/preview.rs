@@ -0,0 +1,316 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Size-bounded display of guest file content
+//!
+//! `cat`, `grep`, the shell and the TUI share these helpers so every command
+//! that shows file content streams it instead of loading it whole, honours
+//! `--max-bytes`, and treats binary files the same way.
+
+use super::output::format_size;
+use anyhow::Result;
+use guestkit::guestfs::FilePreview;
+use regex::Regex;
+use std::collections::VecDeque;
+use std::io::{BufRead, Read, Write};
+
+/// Default `--max-bytes` for commands that print file content
+pub const DEFAULT_MAX_BYTES: &str = "10M";
+
+/// Byte limit for a reader; `0` means unlimited
+pub fn read_limit(max_bytes: u64) -> u64 {
+    if max_bytes == 0 {
+        u64::MAX
+    } else {
+        max_bytes
+    }
+}
+
+/// Show tabs, carriage returns and other control characters as `^X`
+pub fn show_control(line: &str) -> String {
+    line.chars()
+        .map(|c| match c {
+            '\t' => "^I".to_string(),
+            '\r' => "^M".to_string(),
+            c if (c as u32) < 0x20 => format!("^{}", (c as u8 + 64) as char),
+            '\u{7f}' => "^?".to_string(),
+            c => c.to_string(),
+        })
+        .collect()
+}
+
+/// Split a reader into lossily decoded lines, dropping `\n` / `\r\n`
+fn lines<R: BufRead>(reader: R) -> impl Iterator<Item = std::io::Result<String>> {
+    reader.split(b'\n').map(|line| {
+        line.map(|mut bytes| {
+            if bytes.last() == Some(&b'\r') {
+                bytes.pop();
+            }
+            String::from_utf8(bytes)
+                .unwrap_or_else(|e| String::from_utf8_lossy(e.as_bytes()).into_owned())
+        })
+    })
+}
+
+/// Write text lines from a reader, numbering from `first_line`
+///
+/// Returns the number of lines written.
+pub fn write_lines<R: BufRead, W: Write>(
+    reader: R,
+    out: &mut W,
+    first_line: usize,
+    line_numbers: bool,
+    show_all: bool,
+) -> Result<usize> {
+    let mut count = 0;
+    for line in lines(reader) {
+        let line = line?;
+        let line = if show_all { show_control(&line) } else { line };
+        if line_numbers {
+            writeln!(out, "{:6}\t{}", first_line + count, line)?;
+        } else {
+            writeln!(out, "{}", line)?;
+        }
+        count += 1;
+    }
+    Ok(count)
+}
+
+/// Write a hex dump, 16 bytes per row, starting at byte `offset`
+pub fn write_hex<R: Read, W: Write>(
+    mut reader: R,
+    out: &mut W,
+    offset: u64,
+    show_offsets: bool,
+) -> Result<()> {
+    let mut row = [0u8; 16];
+    let mut position = offset;
+    loop {
+        let mut filled = 0;
+        while filled < row.len() {
+            match reader.read(&mut row[filled..])? {
+                0 => break,
+                n => filled += n,
+            }
+        }
+        if filled == 0 {
+            return Ok(());
+        }
+
+        if show_offsets {
+            write!(out, "{:08x}: ", position)?;
+        }
+        for byte in &row[..filled] {
+            write!(out, "{:02x} ", byte)?;
+        }
+        writeln!(out)?;
+        position += filled as u64;
+
+        if filled < row.len() {
+            return Ok(());
+        }
+    }
+}
+
+/// Count newlines without holding the content in memory
+pub fn count_lines<R: Read>(mut reader: R) -> Result<usize> {
+    let mut buffer = [0u8; 64 * 1024];
+    let mut count = 0;
+    loop {
+        match reader.read(&mut buffer)? {
+            0 => return Ok(count),
+            n => count += buffer[..n].iter().filter(|&&b| b == b'\n').count(),
+        }
+    }
+}
+
+/// Notice printed in place of the part of a file that was not read
+pub fn omitted_notice(preview: &FilePreview) -> String {
+    format!(
+        "... {} of {} omitted (use --max-bytes 0 to show the whole file) ...",
+        format_size(preview.omitted_bytes()),
+        format_size(preview.size)
+    )
+}
+
+/// Render a preview as text for views that display a string
+///
+/// Binary content is shown as a hex dump of its first bytes.
+pub fn preview_text(preview: &FilePreview) -> String {
+    if preview.binary {
+        let mut dump = Vec::new();
+        let _ = write_hex(
+            &preview.head[..preview.head.len().min(4096)],
+            &mut dump,
+            0,
+            true,
+        );
+        return format!(
+            "Binary file ({})\n\n{}",
+            format_size(preview.size),
+            String::from_utf8_lossy(&dump)
+        );
+    }
+
+    if !preview.is_truncated() {
+        return preview.head_text();
+    }
+    format!(
+        "{}\n{}\n\n{}",
+        preview.head_text(),
+        omitted_notice(preview),
+        preview.tail_text()
+    )
+}
+
+/// Line reported by [`grep_lines`]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GrepLine {
+    /// Zero-based line number
+    pub line_no: usize,
+    pub text: String,
+    /// Selected line (as opposed to context)
+    pub is_match: bool,
+}
+
+/// Line selection options for [`grep_lines`]
+#[derive(Debug, Clone, Default)]
+pub struct GrepOptions {
+    /// Select lines that do not match
+    pub invert: bool,
+    /// Context lines before each selected line
+    pub before: usize,
+    /// Context lines after each selected line
+    pub after: usize,
+    /// Stop after this many selected lines
+    pub limit: Option<usize>,
+}
+
+/// Stream a reader line by line, emitting selected lines and their context
+///
+/// Only the `before` context window is buffered, so memory use does not
+/// depend on file size. Returns the number of selected lines.
+pub fn grep_lines<R: BufRead>(
+    reader: R,
+    pattern: &Regex,
+    options: &GrepOptions,
+    mut emit: impl FnMut(GrepLine) -> Result<()>,
+) -> Result<usize> {
+    let mut history: VecDeque<(usize, String)> = VecDeque::with_capacity(options.before);
+    let mut after_left = 0;
+    let mut selected = 0;
+
+    for (line_no, text) in lines(reader).enumerate() {
+        let text = text?;
+
+        if options.limit.is_some_and(|limit| selected >= limit) {
+            // Finish the trailing context of the last selected line
+            if after_left == 0 {
+                break;
+            }
+            after_left -= 1;
+            emit(GrepLine {
+                line_no,
+                text,
+                is_match: false,
+            })?;
+            continue;
+        }
+
+        if pattern.is_match(&text) != options.invert {
+            selected += 1;
+            for (line_no, text) in history.drain(..) {
+                emit(GrepLine {
+                    line_no,
+                    text,
+                    is_match: false,
+                })?;
+            }
+            emit(GrepLine {
+                line_no,
+                text,
+                is_match: true,
+            })?;
+            after_left = options.after;
+        } else if after_left > 0 {
+            after_left -= 1;
+            emit(GrepLine {
+                line_no,
+                text,
+                is_match: false,
+            })?;
+        } else if options.before > 0 {
+            if history.len() == options.before {
+                history.pop_front();
+            }
+            history.push_back((line_no, text));
+        }
+    }
+
+    Ok(selected)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn grep(content: &str, pattern: &str, options: GrepOptions) -> Vec<(usize, bool)> {
+        let re = Regex::new(pattern).unwrap();
+        let mut found = Vec::new();
+        grep_lines(content.as_bytes(), &re, &options, |line| {
+            found.push((line.line_no, line.is_match));
+            Ok(())
+        })
+        .unwrap();
+        found
+    }
+
+    #[test]
+    fn test_grep_context_without_duplicates() {
+        let content = "a\nb\nmatch1\nc\nmatch2\nd\ne\nf\n";
+        let options = GrepOptions {
+            before: 1,
+            after: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            grep(content, "match", options),
+            vec![(1, false), (2, true), (3, false), (4, true), (5, false)]
+        );
+    }
+
+    #[test]
+    fn test_grep_limit_and_invert() {
+        let content = "x\ny\nx\ny\n";
+        let limited = GrepOptions {
+            limit: Some(1),
+            after: 1,
+            ..Default::default()
+        };
+        assert_eq!(grep(content, "x", limited), vec![(0, true), (1, false)]);
+
+        let inverted = GrepOptions {
+            invert: true,
+            ..Default::default()
+        };
+        assert_eq!(grep(content, "x", inverted), vec![(1, true), (3, true)]);
+    }
+
+    #[test]
+    fn test_write_lines_and_hex() {
+        let mut out = Vec::new();
+        let n = write_lines("one\r\ntwo\tx\n".as_bytes(), &mut out, 5, true, true).unwrap();
+        assert_eq!(n, 2);
+        assert_eq!(
+            String::from_utf8(out).unwrap(),
+            "     5\tone\n     6\ttwo^Ix\n"
+        );
+
+        let mut out = Vec::new();
+        write_hex(&[0u8, 1, 0xff][..], &mut out, 16, true).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(), "00000010: 00 01 ff \n");
+    }
+
+    #[test]
+    fn test_count_lines() {
+        assert_eq!(count_lines("a\nb\nc".as_bytes()).unwrap(), 2);
+    }
+}
@@ -0,0 +1,392 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Distro package manifest loading (Debian `Packages`, RPM repodata)
+
+use anyhow::{bail, Context, Result};
+use regex::Regex;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// Package versions published by the loaded manifests
+#[derive(Debug, Default)]
+pub struct ManifestIndex {
+    versions: HashMap<String, HashSet<String>>,
+}
+
+impl ManifestIndex {
+    pub fn insert(&mut self, name: &str, version: &str) {
+        self.versions
+            .entry(name.to_string())
+            .or_default()
+            .insert(version.to_string());
+    }
+
+    pub fn extend(&mut self, packages: Vec<(String, String)>) {
+        for (name, version) in packages {
+            self.insert(&name, &version);
+        }
+    }
+
+    pub fn versions(&self, name: &str) -> Option<&HashSet<String>> {
+        self.versions.get(name)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.versions.is_empty()
+    }
+}
+
+/// Kind of manifest document
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManifestKind {
+    /// Debian/Ubuntu `Packages` index
+    DebPackages,
+    /// RPM repodata `repomd.xml` pointing at primary metadata
+    RpmRepomd,
+    /// RPM repodata `primary.xml`
+    RpmPrimary,
+}
+
+impl ManifestKind {
+    /// Detect the manifest kind from its (decompressed) content
+    pub fn detect(content: &str) -> Option<Self> {
+        let head: String = content.chars().take(512).collect();
+        if head.contains("<repomd") {
+            Some(Self::RpmRepomd)
+        } else if head.contains("<metadata") {
+            Some(Self::RpmPrimary)
+        } else if content.starts_with("Package:") || head.contains("\nPackage:") {
+            Some(Self::DebPackages)
+        } else {
+            None
+        }
+    }
+}
+
+/// Load a manifest from a URL or local path, following repomd.xml to primary
+///
+/// Returns the `(name, version)` pairs it publishes.
+pub fn load(location: &str) -> Result<Vec<(String, String)>> {
+    let content = read_location(location)?;
+
+    match ManifestKind::detect(&content) {
+        Some(ManifestKind::DebPackages) => Ok(parse_deb_packages(&content)),
+        Some(ManifestKind::RpmPrimary) => Ok(parse_rpm_primary(&content)),
+        Some(ManifestKind::RpmRepomd) => {
+            let href = primary_href(&content)
+                .with_context(|| format!("{}: no primary metadata listed", location))?;
+            let base = repo_base(location);
+            let primary = read_location(&join_location(&base, &href))?;
+            Ok(parse_rpm_primary(&primary))
+        }
+        None => bail!("{}: not a Packages index or RPM repodata", location),
+    }
+}
+
+/// Manifest locations under a Debian/Ubuntu mirror for a release
+pub fn deb_locations(mirror: &str, distro: &str, codename: &str, arch: &str) -> Vec<String> {
+    let components: &[&str] = if distro == "ubuntu" {
+        &["main", "restricted", "universe", "multiverse"]
+    } else {
+        &["main", "contrib", "non-free", "non-free-firmware"]
+    };
+
+    let suites = [
+        codename.to_string(),
+        format!("{}-updates", codename),
+        format!("{}-security", codename),
+    ];
+
+    let mut locations = Vec::new();
+    for suite in &suites {
+        for component in components {
+            locations.push(join_location(
+                mirror,
+                &format!("dists/{}/{}/binary-{}/Packages", suite, component, arch),
+            ));
+        }
+    }
+    locations
+}
+
+/// Repository bases under an RPM mirror
+///
+/// A URL is taken as a single repository. A local directory may contain
+/// several repositories (e.g. `BaseOS/`, `AppStream/`), found by looking for
+/// `repodata/repomd.xml` up to three levels down.
+pub fn rpm_locations(mirror: &str) -> Vec<String> {
+    if is_url(mirror) {
+        return vec![join_location(mirror, "repodata/repomd.xml")];
+    }
+
+    let mut found = Vec::new();
+    find_repodata(Path::new(mirror), 3, &mut found);
+    found.sort();
+    found
+        .into_iter()
+        .map(|p| p.to_string_lossy().to_string())
+        .collect()
+}
+
+fn find_repodata(dir: &Path, depth: usize, found: &mut Vec<PathBuf>) {
+    let repomd = dir.join("repodata").join("repomd.xml");
+    if repomd.is_file() {
+        found.push(repomd);
+        return;
+    }
+    if depth == 0 {
+        return;
+    }
+    if let Ok(entries) = std::fs::read_dir(dir) {
+        for entry in entries.flatten() {
+            if entry.path().is_dir() {
+                find_repodata(&entry.path(), depth - 1, found);
+            }
+        }
+    }
+}
+
+/// Parse a Debian `Packages` index into `(name, version)` pairs
+pub fn parse_deb_packages(content: &str) -> Vec<(String, String)> {
+    let mut packages = Vec::new();
+    let mut name = None;
+    let mut version = None;
+
+    for line in content.lines().chain(std::iter::once("")) {
+        if line.is_empty() {
+            if let (Some(n), Some(v)) = (name.take(), version.take()) {
+                packages.push((n, v));
+            }
+            name = None;
+            version = None;
+        } else if let Some(value) = line.strip_prefix("Package:") {
+            name = Some(value.trim().to_string());
+        } else if let Some(value) = line.strip_prefix("Version:") {
+            version = Some(value.trim().to_string());
+        }
+    }
+
+    packages
+}
+
+/// Parse RPM `primary.xml` into `(name, [epoch:]version-release)` pairs
+pub fn parse_rpm_primary(content: &str) -> Vec<(String, String)> {
+    let re = Regex::new(
+        r#"(?s)<package type="rpm">\s*<name>([^<]+)</name>.*?<version epoch="(\d*)" ver="([^"]+)" rel="([^"]+)"\s*/>"#,
+    )
+    .unwrap();
+
+    re.captures_iter(content)
+        .map(|caps| (caps[1].to_string(), rpm_evr(&caps[2], &caps[3], &caps[4])))
+        .collect()
+}
+
+/// Format an RPM version as `[epoch:]version-release`, omitting epoch 0
+pub fn rpm_evr(epoch: &str, version: &str, release: &str) -> String {
+    match epoch {
+        "" | "0" | "(none)" => format!("{}-{}", version, release),
+        epoch => format!("{}:{}-{}", epoch, version, release),
+    }
+}
+
+fn primary_href(repomd: &str) -> Option<String> {
+    let re = Regex::new(r#"(?s)<data type="primary">.*?<location href="([^"]+)""#).unwrap();
+    re.captures(repomd).map(|caps| caps[1].to_string())
+}
+
+/// Repository base of a repomd.xml location (strips `repodata/repomd.xml`)
+fn repo_base(location: &str) -> String {
+    location
+        .trim_end_matches("repomd.xml")
+        .trim_end_matches('/')
+        .trim_end_matches("repodata")
+        .trim_end_matches('/')
+        .to_string()
+}
+
+fn is_url(location: &str) -> bool {
+    location.starts_with("http://") || location.starts_with("https://")
+}
+
+fn join_location(base: &str, relative: &str) -> String {
+    if is_url(base) {
+        format!("{}/{}", base.trim_end_matches('/'), relative)
+    } else {
+        Path::new(base).join(relative).to_string_lossy().to_string()
+    }
+}
+
+/// Read and decompress a manifest
+///
+/// Tries the location as given, then with `.gz`, `.xz` and `.zst`
+/// suffixes, since mirrors usually publish only compressed indexes.
+fn read_location(location: &str) -> Result<String> {
+    let tmp = tempfile::tempdir()?;
+    let mut errors = Vec::new();
+
+    for suffix in ["", ".gz", ".xz", ".zst"] {
+        let candidate = format!("{}{}", location, suffix);
+        let local = if is_url(&candidate) {
+            let dest = tmp.path().join("manifest");
+            match download(&candidate, &dest) {
+                Ok(()) => dest,
+                Err(e) => {
+                    errors.push(e.to_string());
+                    continue;
+                }
+            }
+        } else {
+            let path = PathBuf::from(&candidate);
+            if !path.is_file() {
+                continue;
+            }
+            path
+        };
+
+        return decompress(&local).with_context(|| format!("reading {}", candidate));
+    }
+
+    if errors.is_empty() {
+        bail!("{}: not found", location)
+    } else {
+        bail!("{}: {}", location, errors.join("; "))
+    }
+}
+
+/// Download with the host `curl`
+fn download(url: &str, dest: &Path) -> Result<()> {
+    let output = Command::new("curl")
+        .args(["-fsSL", "--retry", "2", "-o"])
+        .arg(dest)
+        .arg(url)
+        .output()
+        .context("failed to run curl")?;
+
+    if !output.status.success() {
+        bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+/// Decompress gzip/xz/zstd by magic bytes with the host tools
+fn decompress(path: &Path) -> Result<String> {
+    let bytes = std::fs::read(path)?;
+
+    let tool = if bytes.starts_with(&[0x1f, 0x8b]) {
+        "gzip"
+    } else if bytes.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+        "xz"
+    } else if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        "zstd"
+    } else {
+        return Ok(String::from_utf8_lossy(&bytes).to_string());
+    };
+
+    let output = Command::new(tool)
+        .arg("-dc")
+        .arg(path)
+        .output()
+        .with_context(|| format!("failed to run {}", tool))?;
+
+    if !output.status.success() {
+        bail!(
+            "{}: {}",
+            tool,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+
+    Ok(String::from_utf8_lossy(&output.stdout).to_string())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_deb_packages() {
+        let content = "Package: bash\nVersion: 5.2.15-2+b2\nArchitecture: amd64\n\n\
+                       Package: tzdata\nArchitecture: all\nVersion: 2024a-0+deb12u1\n";
+        let packages = parse_deb_packages(content);
+
+        assert_eq!(
+            packages,
+            vec![
+                ("bash".to_string(), "5.2.15-2+b2".to_string()),
+                ("tzdata".to_string(), "2024a-0+deb12u1".to_string()),
+            ]
+        );
+        assert_eq!(
+            ManifestKind::detect(content),
+            Some(ManifestKind::DebPackages)
+        );
+    }
+
+    #[test]
+    fn test_parse_rpm_primary() {
+        let content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<metadata xmlns="http://linux.duke.edu/metadata/common" packages="2">
+<package type="rpm">
+  <name>bash</name>
+  <arch>x86_64</arch>
+  <version epoch="0" ver="5.2.26" rel="3.fc40"/>
+</package>
+<package type="rpm">
+  <name>openssl</name>
+  <arch>x86_64</arch>
+  <version epoch="1" ver="3.2.1" rel="2.fc40"/>
+</package>
+</metadata>"#;
+
+        assert_eq!(
+            ManifestKind::detect(content),
+            Some(ManifestKind::RpmPrimary)
+        );
+        assert_eq!(
+            parse_rpm_primary(content),
+            vec![
+                ("bash".to_string(), "5.2.26-3.fc40".to_string()),
+                ("openssl".to_string(), "1:3.2.1-2.fc40".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_repomd_primary_href() {
+        let repomd = r#"<?xml version="1.0" encoding="UTF-8"?>
+<repomd xmlns="http://linux.duke.edu/metadata/repo">
+  <data type="filelists">
+    <location href="repodata/abc-filelists.xml.zst"/>
+  </data>
+  <data type="primary">
+    <checksum type="sha256">abc</checksum>
+    <location href="repodata/def-primary.xml.zst"/>
+  </data>
+</repomd>"#;
+
+        assert_eq!(ManifestKind::detect(repomd), Some(ManifestKind::RpmRepomd));
+        assert_eq!(
+            primary_href(repomd).as_deref(),
+            Some("repodata/def-primary.xml.zst")
+        );
+        assert_eq!(
+            repo_base("https://mirror/fedora/40/Everything/x86_64/os/repodata/repomd.xml"),
+            "https://mirror/fedora/40/Everything/x86_64/os"
+        );
+    }
+
+    #[test]
+    fn test_deb_locations() {
+        let locations = deb_locations(
+            "https://deb.debian.org/debian/",
+            "debian",
+            "bookworm",
+            "amd64",
+        );
+        assert_eq!(locations.len(), 12);
+        assert_eq!(
+            locations[0],
+            "https://deb.debian.org/debian/dists/bookworm/main/binary-amd64/Packages"
+        );
+    }
+}
@@ -0,0 +1,444 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Image comparison against upstream distro package manifests
+//!
+//! Reports which installed packages are not published by the distro's
+//! official repositories and which packaged files no longer match their
+//! pristine checksums.
+
+pub mod manifest;
+pub mod reporter;
+pub mod verify;
+
+use crate::cli::plain;
+use crate::cli::target;
+use anyhow::Result;
+use guestkit::Guestfs;
+use manifest::ManifestIndex;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
+use verify::InstalledPackage;
+
+/// Where an installed package comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PackageOrigin {
+    /// Name and version are published by the official repositories
+    Official,
+    /// Name is published, but not this version (superseded or rebuilt)
+    UnpublishedVersion,
+    /// Name is not published by the official repositories
+    Foreign,
+}
+
+impl PackageOrigin {
+    pub fn emoji(&self) -> &str {
+        match self {
+            Self::Official => "✅",
+            Self::UnpublishedVersion => "⚠️",
+            Self::Foreign => "❌",
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Official => "official",
+            Self::UnpublishedVersion => "unpublished-version",
+            Self::Foreign => "foreign",
+        }
+    }
+}
+
+/// Installed package classified against the manifests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PackageStatus {
+    pub name: String,
+    pub version: String,
+    pub arch: String,
+    pub origin: PackageOrigin,
+    /// Published versions, when the installed one is not among them
+    pub published_versions: Vec<String>,
+}
+
+/// Packaged file that differs from its pristine checksum
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileDeviation {
+    pub path: String,
+    /// Owning package, when known
+    pub package: Option<String>,
+    /// Content changed; false means the file is missing or unreadable
+    pub modified: bool,
+    /// Marked as a configuration file by the package
+    pub config: bool,
+}
+
+/// Pristine comparison summary
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct PristineSummary {
+    pub total_packages: usize,
+    pub official_packages: usize,
+    pub unpublished_versions: usize,
+    pub foreign_packages: usize,
+    pub files_checked: usize,
+    pub files_modified: usize,
+    pub files_missing: usize,
+    pub config_files_modified: usize,
+    /// Share of installed packages published by the official repositories
+    pub official_percent: f64,
+    /// Share of checked files matching their pristine checksum
+    pub file_integrity_percent: Option<f64>,
+}
+
+/// Pristine comparison report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct PristineReport {
+    pub image_path: String,
+    pub scanned_at: String,
+    pub distro: String,
+    pub version: String,
+    pub arch: String,
+    pub package_format: String,
+    /// Manifest locations that were loaded
+    pub manifests: Vec<String>,
+    pub packages: Vec<PackageStatus>,
+    pub file_deviations: Vec<FileDeviation>,
+    pub summary: PristineSummary,
+    /// Manifests or checks that could not be read
+    pub warnings: Vec<String>,
+}
+
+/// Options for a pristine comparison
+#[derive(Debug, Clone, Default)]
+pub struct PristineOptions {
+    /// Mirror URL or local mirror path (distro default if not set)
+    pub mirror: Option<String>,
+    /// Explicit manifest locations, used instead of the mirror layout
+    pub manifests: Vec<String>,
+    /// Verify installed files against package checksums
+    pub verify_files: bool,
+    pub verbose: bool,
+}
+
+/// Compare an image against its distro's published package manifests
+pub fn scan_pristine<P: AsRef<Path>>(
+    image_path: P,
+    options: &PristineOptions,
+) -> Result<PristineReport> {
+    let image_path_str = image_path.as_ref().display().to_string();
+
+    if options.verbose {
+        println!("{}Comparing against upstream manifests: {}", plain::text("🔍 "), image_path_str);
+    }
+
+    // Initialize guestfs
+    let mut g = Guestfs::new()?;
+    g.add_drive_opts(&image_path, true, None)?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
+    g.launch()?;
+
+    // Inspect OS
+    let roots = g.inspect_os()?;
+    if roots.is_empty() {
+        anyhow::bail!("No operating systems found in disk image");
+    }
+
+    let root = &roots[0];
+    let distro = g
+        .inspect_get_distro(root)
+        .unwrap_or_else(|_| "unknown".to_string());
+    let major = g.inspect_get_major_version(root).unwrap_or(0);
+    let arch = g
+        .inspect_get_arch(root)
+        .unwrap_or_else(|_| "x86_64".to_string());
+    let package_format = g.inspect_get_package_format(root)?;
+
+    if package_format != "deb" && package_format != "rpm" {
+        anyhow::bail!("Unsupported package format: {}", package_format);
+    }
+
+    // Mount filesystems
+    g.mount_os_ro(root)?;
+
+    let codename = g
+        .read_file("/etc/os-release")
+        .ok()
+        .and_then(|content| os_release_codename(&String::from_utf8_lossy(&content)));
+
+    let installed = verify::installed_packages(&mut g, &package_format)?;
+
+    let mut warnings = Vec::new();
+    let (file_deviations, files_checked) = if options.verify_files {
+        if options.verbose {
+            println!("{}Verifying {} packages' files", plain::text("📁 "), installed.len());
+        }
+        match verify::verify_files(&mut g, &package_format, &installed) {
+            Ok(result) => result,
+            Err(e) => {
+                warnings.push(format!("file verification: {}", e));
+                (Vec::new(), 0)
+            }
+        }
+    } else {
+        (Vec::new(), 0)
+    };
+
+    // Shutdown guestfs
+    g.shutdown()?;
+
+    let locations = if !options.manifests.is_empty() {
+        options.manifests.clone()
+    } else {
+        let mirror = match options.mirror.clone() {
+            Some(mirror) => mirror,
+            None => default_mirror(&distro, major, &arch)?,
+        };
+        match package_format.as_str() {
+            "deb" => {
+                let codename = codename.clone().ok_or_else(|| {
+                    anyhow::anyhow!("Cannot determine release codename; use --manifest")
+                })?;
+                manifest::deb_locations(&mirror, &distro, &codename, &deb_arch(&arch))
+            }
+            _ => {
+                let mut locations = manifest::rpm_locations(&mirror);
+                // Fedora publishes updates in a separate tree
+                if options.mirror.is_none() && distro == "fedora" {
+                    locations.extend(manifest::rpm_locations(&fedora_updates_mirror(
+                        major, &arch,
+                    )));
+                }
+                locations
+            }
+        }
+    };
+
+    if locations.is_empty() {
+        anyhow::bail!("No package manifests found for the given mirror");
+    }
+
+    let mut index = ManifestIndex::default();
+    let mut manifests = Vec::new();
+    for location in &locations {
+        if options.verbose {
+            println!("{}Loading {}", plain::text("📥 "), location);
+        }
+        match manifest::load(location) {
+            Ok(packages) => {
+                index.extend(packages);
+                manifests.push(location.clone());
+            }
+            Err(e) => warnings.push(e.to_string()),
+        }
+    }
+
+    if index.is_empty() {
+        anyhow::bail!(
+            "No package manifests could be loaded:\n{}",
+            warnings.join("\n")
+        );
+    }
+
+    let packages = classify(&installed, &index);
+    let summary = summarize(
+        &packages,
+        &file_deviations,
+        files_checked,
+        options.verify_files,
+    );
+
+    Ok(PristineReport {
+        image_path: image_path_str,
+        scanned_at: chrono::Utc::now().to_rfc3339(),
+        distro,
+        version: codename.unwrap_or_else(|| major.to_string()),
+        arch,
+        package_format,
+        manifests,
+        packages,
+        file_deviations,
+        summary,
+        warnings,
+    })
+}
+
+/// Classify installed packages against the published manifests
+pub fn classify(installed: &[InstalledPackage], index: &ManifestIndex) -> Vec<PackageStatus> {
+    let mut packages: Vec<PackageStatus> = installed
+        .iter()
+        .map(|pkg| {
+            let (origin, published_versions) = match index.versions(&pkg.name) {
+                Some(versions) if versions.contains(&pkg.version) => {
+                    (PackageOrigin::Official, Vec::new())
+                }
+                Some(versions) => {
+                    let mut versions: Vec<String> = versions.iter().cloned().collect();
+                    versions.sort();
+                    (PackageOrigin::UnpublishedVersion, versions)
+                }
+                None => (PackageOrigin::Foreign, Vec::new()),
+            };
+            PackageStatus {
+                name: pkg.name.clone(),
+                version: pkg.version.clone(),
+                arch: pkg.arch.clone(),
+                origin,
+                published_versions,
+            }
+        })
+        .collect();
+
+    packages.sort_by(|a, b| a.name.cmp(&b.name).then_with(|| a.arch.cmp(&b.arch)));
+    packages
+}
+
+/// Build summary statistics for a pristine comparison
+pub fn summarize(
+    packages: &[PackageStatus],
+    deviations: &[FileDeviation],
+    files_checked: usize,
+    verified: bool,
+) -> PristineSummary {
+    let count = |origin: PackageOrigin| packages.iter().filter(|p| p.origin == origin).count();
+    let official = count(PackageOrigin::Official);
+    let files_modified = deviations.iter().filter(|d| d.modified).count();
+
+    PristineSummary {
+        total_packages: packages.len(),
+        official_packages: official,
+        unpublished_versions: count(PackageOrigin::UnpublishedVersion),
+        foreign_packages: count(PackageOrigin::Foreign),
+        files_checked,
+        files_modified,
+        files_missing: deviations.len() - files_modified,
+        config_files_modified: deviations.iter().filter(|d| d.modified && d.config).count(),
+        official_percent: percent(official, packages.len()),
+        file_integrity_percent: (verified && files_checked > 0).then(|| {
+            percent(
+                files_checked.saturating_sub(deviations.len()),
+                files_checked,
+            )
+        }),
+    }
+}
+
+fn percent(part: usize, total: usize) -> f64 {
+    if total == 0 {
+        return 100.0;
+    }
+    (part as f64 / total as f64 * 1000.0).round() / 10.0
+}
+
+/// Release codename from os-release (`VERSION_CODENAME`, or `UBUNTU_CODENAME`)
+fn os_release_codename(content: &str) -> Option<String> {
+    ["VERSION_CODENAME=", "UBUNTU_CODENAME="]
+        .iter()
+        .find_map(|key| {
+            content
+                .lines()
+                .find_map(|line| line.strip_prefix(key))
+                .map(|value| value.trim().trim_matches('"').to_string())
+                .filter(|value| !value.is_empty())
+        })
+}
+
+/// Debian architecture name for an inspected architecture
+fn deb_arch(arch: &str) -> String {
+    match arch {
+        "x86_64" => "amd64",
+        "aarch64" => "arm64",
+        "i386" | "i486" | "i586" | "i686" => "i386",
+        "armv7l" => "armhf",
+        "ppc64le" => "ppc64el",
+        other => other,
+    }
+    .to_string()
+}
+
+/// Default public mirror for distros with a well-known layout
+fn default_mirror(distro: &str, major: i32, arch: &str) -> Result<String> {
+    match distro {
+        "debian" => Ok("https://deb.debian.org/debian".to_string()),
+        "ubuntu" if arch == "x86_64" || arch == "i686" => {
+            Ok("http://archive.ubuntu.com/ubuntu".to_string())
+        }
+        "ubuntu" => Ok("http://ports.ubuntu.com/ubuntu-ports".to_string()),
+        "fedora" => Ok(format!(
+            "https://dl.fedoraproject.org/pub/fedora/linux/releases/{}/Everything/{}/os",
+            major, arch
+        )),
+        other => anyhow::bail!(
+            "No default mirror for distro '{}'; use --mirror or --manifest",
+            other
+        ),
+    }
+}
+
+fn fedora_updates_mirror(major: i32, arch: &str) -> String {
+    format!(
+        "https://dl.fedoraproject.org/pub/fedora/linux/updates/{}/Everything/{}",
+        major, arch
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn installed(name: &str, version: &str) -> InstalledPackage {
+        InstalledPackage {
+            name: name.to_string(),
+            version: version.to_string(),
+            arch: "amd64".to_string(),
+            conffiles: Vec::new(),
+        }
+    }
+
+    #[test]
+    fn test_classify() {
+        let mut index = ManifestIndex::default();
+        index.insert("bash", "5.2.15-2+b2");
+        index.insert("openssl", "3.0.13-1~deb12u1");
+
+        let packages = classify(
+            &[
+                installed("openssl", "3.0.11-1~deb12u2"),
+                installed("bash", "5.2.15-2+b2"),
+                installed("custom-agent", "1.0"),
+            ],
+            &index,
+        );
+
+        assert_eq!(packages[0].name, "bash");
+        assert_eq!(packages[0].origin, PackageOrigin::Official);
+        assert_eq!(packages[1].origin, PackageOrigin::Foreign);
+        assert_eq!(packages[2].origin, PackageOrigin::UnpublishedVersion);
+        assert_eq!(
+            packages[2].published_versions,
+            vec!["3.0.13-1~deb12u1".to_string()]
+        );
+
+        let deviations = vec![FileDeviation {
+            path: "/etc/ssh/sshd_config".to_string(),
+            package: Some("openssh-server".to_string()),
+            modified: true,
+            config: true,
+        }];
+        let summary = summarize(&packages, &deviations, 200, true);
+        assert_eq!(summary.official_packages, 1);
+        assert_eq!(summary.official_percent, 33.3);
+        assert_eq!(summary.config_files_modified, 1);
+        assert_eq!(summary.files_missing, 0);
+        assert_eq!(summary.file_integrity_percent, Some(99.5));
+    }
+
+    #[test]
+    fn test_os_release_codename() {
+        let debian = "PRETTY_NAME=\"Debian GNU/Linux 12 (bookworm)\"\nVERSION_CODENAME=bookworm\n";
+        assert_eq!(os_release_codename(debian).as_deref(), Some("bookworm"));
+
+        let ubuntu = "VERSION_CODENAME=\nUBUNTU_CODENAME=noble\n";
+        assert_eq!(os_release_codename(ubuntu).as_deref(), Some("noble"));
+
+        assert_eq!(os_release_codename("ID=fedora\n"), None);
+        assert_eq!(deb_arch("x86_64"), "amd64");
+    }
+}
@@ -0,0 +1,162 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Pristine comparison report formatting
+
+use super::{PackageOrigin, PristineReport};
+
+/// Number of packages or files listed per section in the text report
+const MAX_LISTED: usize = 50;
+
+/// Format pristine comparison report as text
+pub fn format_report(report: &PristineReport) -> String {
+    let mut output = String::new();
+
+    output.push_str("🧪 Pristine Image Report\n");
+    output.push_str("========================\n\n");
+    output.push_str(&format!("Image: {}\n", report.image_path));
+    output.push_str(&format!(
+        "OS: {} {} ({}, {})\n",
+        report.distro, report.version, report.arch, report.package_format
+    ));
+    output.push_str(&format!("Scanned: {}\n", report.scanned_at));
+    output.push_str(&format!("Manifests loaded: {}\n\n", report.manifests.len()));
+
+    // Summary
+    let summary = &report.summary;
+    output.push_str("📊 Summary\n");
+    output.push_str("----------\n");
+    output.push_str(&format!("Installed packages: {}\n", summary.total_packages));
+    output.push_str(&format!(
+        "Official: {} ({:.1}%)\n",
+        summary.official_packages, summary.official_percent
+    ));
+    output.push_str(&format!(
+        "Unpublished versions: {}\n",
+        summary.unpublished_versions
+    ));
+    output.push_str(&format!(
+        "Not from official repos: {}\n",
+        summary.foreign_packages
+    ));
+    match summary.file_integrity_percent {
+        Some(integrity) => {
+            output.push_str(&format!("Files checked: {}\n", summary.files_checked));
+            output.push_str(&format!(
+                "Modified: {} ({} config)\n",
+                summary.files_modified, summary.config_files_modified
+            ));
+            output.push_str(&format!("Missing: {}\n", summary.files_missing));
+            output.push_str(&format!("File integrity: {:.1}%\n", integrity));
+        }
+        None => output.push_str("Files: not verified\n"),
+    }
+    output.push('\n');
+
+    for (origin, title) in [
+        (PackageOrigin::Foreign, "Packages Not From Official Repos"),
+        (
+            PackageOrigin::UnpublishedVersion,
+            "Unpublished Package Versions",
+        ),
+    ] {
+        let packages: Vec<_> = report
+            .packages
+            .iter()
+            .filter(|p| p.origin == origin)
+            .collect();
+        if packages.is_empty() {
+            continue;
+        }
+
+        let heading = format!("{} {} ({})", origin.emoji(), title, packages.len());
+        output.push_str(&format!("{}\n", heading));
+        output.push_str(&format!("{}\n", "-".repeat(heading.chars().count())));
+        for package in packages.iter().take(MAX_LISTED) {
+            output.push_str(&format!(
+                "{} {} [{}]\n",
+                package.name, package.version, package.arch
+            ));
+            if !package.published_versions.is_empty() {
+                output.push_str(&format!(
+                    "   └─ published: {}\n",
+                    package.published_versions.join(", ")
+                ));
+            }
+        }
+        if packages.len() > MAX_LISTED {
+            output.push_str(&format!("... and {} more\n", packages.len() - MAX_LISTED));
+        }
+        output.push('\n');
+    }
+
+    if !report.file_deviations.is_empty() {
+        output.push_str(&format!(
+            "📝 Changed Files ({})\n",
+            report.file_deviations.len()
+        ));
+        output.push_str("-----------------\n");
+        for deviation in report.file_deviations.iter().take(MAX_LISTED) {
+            let state = if deviation.modified {
+                "modified"
+            } else {
+                "missing"
+            };
+            let kind = if deviation.config { " config" } else { "" };
+            output.push_str(&format!(
+                "{} [{}{}] {}\n",
+                deviation.path,
+                state,
+                kind,
+                deviation.package.as_deref().unwrap_or("unowned")
+            ));
+        }
+        if report.file_deviations.len() > MAX_LISTED {
+            output.push_str(&format!(
+                "... and {} more\n",
+                report.file_deviations.len() - MAX_LISTED
+            ));
+        }
+        output.push('\n');
+    }
+
+    if !report.warnings.is_empty() {
+        output.push_str("⚠️  Warnings\n");
+        output.push_str("-----------\n");
+        for warning in &report.warnings {
+            output.push_str(&format!("{}\n", warning));
+        }
+        output.push('\n');
+    }
+
+    output
+}
+
+/// Format package classification and file deviations as CSV
+pub fn format_csv(report: &PristineReport) -> String {
+    let mut output = String::from("Type,Name,Version,Arch,Status,Package,Config\n");
+
+    for package in &report.packages {
+        output.push_str(&format!(
+            "package,\"{}\",\"{}\",{},{},,\n",
+            package.name,
+            package.version,
+            package.arch,
+            package.origin.as_str()
+        ));
+    }
+
+    for deviation in &report.file_deviations {
+        output.push_str(&format!(
+            "file,\"{}\",,,{},\"{}\",{}\n",
+            deviation.path.replace('"', "\"\""),
+            if deviation.modified {
+                "modified"
+            } else {
+                "missing"
+            },
+            deviation.package.as_deref().unwrap_or(""),
+            deviation.config
+        ));
+    }
+
+    output
+}
@@ -0,0 +1,356 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Installed package and file verification inside the guest
+
+use super::manifest::rpm_evr;
+use super::FileDeviation;
+use anyhow::{Context, Result};
+use guestkit::core::escape::shell_quote;
+use guestkit::Guestfs;
+use std::collections::HashMap;
+
+/// Installed package as recorded by the guest package database
+#[derive(Debug, Clone, PartialEq)]
+pub struct InstalledPackage {
+    pub name: String,
+    pub version: String,
+    pub arch: String,
+    /// Conffiles with their pristine MD5 (dpkg only)
+    pub conffiles: Vec<(String, String)>,
+}
+
+/// List installed packages from dpkg status or the RPM database
+pub fn installed_packages(g: &mut Guestfs, package_format: &str) -> Result<Vec<InstalledPackage>> {
+    match package_format {
+        "deb" => {
+            let status = g
+                .read_file("/var/lib/dpkg/status")
+                .context("reading /var/lib/dpkg/status")?;
+            Ok(parse_dpkg_status(&String::from_utf8_lossy(&status)))
+        }
+        "rpm" => {
+            let output = g
+                .command(&[
+                    "rpm",
+                    "-qa",
+                    "--qf",
+                    "%{NAME}\\t%{EPOCHNUM}\\t%{VERSION}\\t%{RELEASE}\\t%{ARCH}\\n",
+                ])
+                .context("querying the RPM database")?;
+            Ok(parse_rpm_query(&output))
+        }
+        other => anyhow::bail!("Unsupported package format: {}", other),
+    }
+}
+
+/// Verify installed files against the package database checksums
+///
+/// Returns the deviations and the number of files checked.
+pub fn verify_files(
+    g: &mut Guestfs,
+    package_format: &str,
+    packages: &[InstalledPackage],
+) -> Result<(Vec<FileDeviation>, usize)> {
+    match package_format {
+        "deb" => verify_deb_files(g, packages),
+        "rpm" => verify_rpm_files(g),
+        other => anyhow::bail!("Unsupported package format: {}", other),
+    }
+}
+
+fn verify_deb_files(
+    g: &mut Guestfs,
+    packages: &[InstalledPackage],
+) -> Result<(Vec<FileDeviation>, usize)> {
+    // md5sum exits non-zero on any mismatch, so always succeed to keep stdout
+    let output = g.sh("cd / && for f in /var/lib/dpkg/info/*.md5sums; do \
+         p=${f##*/}; p=${p%.md5sums}; \
+         md5sum -c --quiet \"$f\" 2>/dev/null | sed \"s|^|$p\\t|\"; \
+         done; true")?;
+    let mut deviations = parse_md5sum_failures(&output);
+
+    let count = g.sh("cat /var/lib/dpkg/info/*.md5sums 2>/dev/null | wc -l")?;
+    let mut checked: usize = count.trim().parse().unwrap_or(0);
+
+    // Conffiles are excluded from md5sums; check them against dpkg status
+    let conffiles: Vec<(&str, &str, &str)> = packages
+        .iter()
+        .flat_map(|p| {
+            p.conffiles
+                .iter()
+                .map(move |(path, md5)| (p.name.as_str(), path.as_str(), md5.as_str()))
+        })
+        .collect();
+
+    if !conffiles.is_empty() {
+        let paths: Vec<String> = conffiles
+            .iter()
+            .map(|(_, path, _)| shell_quote(path))
+            .collect();
+        let output = g.sh(&format!("md5sum {} 2>/dev/null; true", paths.join(" ")))?;
+        let actual = parse_md5sum_output(&output);
+
+        for (package, path, expected) in conffiles {
+            checked += 1;
+            let modified = match actual.get(path) {
+                Some(md5) if *md5 == expected => continue,
+                Some(_) => true,
+                None => false,
+            };
+            deviations.push(FileDeviation {
+                path: path.to_string(),
+                package: Some(package.to_string()),
+                modified,
+                config: true,
+            });
+        }
+    }
+
+    Ok((deviations, checked))
+}
+
+fn verify_rpm_files(g: &mut Guestfs) -> Result<(Vec<FileDeviation>, usize)> {
+    // rpm -V exits non-zero when anything differs, so always succeed
+    let output = g.sh("rpm -Va --nomtime --nodeps --noscripts --nosignature 2>/dev/null; true")?;
+    let mut deviations = parse_rpm_verify(&output);
+
+    if !deviations.is_empty() {
+        let paths: Vec<String> = deviations.iter().map(|d| shell_quote(&d.path)).collect();
+        let owners = g.sh(&format!(
+            "for f in {}; do o=$(rpm -qf --qf '%{{NAME}}\\n' \"$f\" 2>/dev/null | head -n1); \
+             printf '%s\\t%s\\n' \"$f\" \"$o\"; done",
+            paths.join(" ")
+        ))?;
+        let owners: HashMap<&str, &str> = owners
+            .lines()
+            .filter_map(|line| line.split_once('\t'))
+            .filter(|(_, owner)| !owner.is_empty())
+            .collect();
+
+        for deviation in &mut deviations {
+            deviation.package = owners.get(deviation.path.as_str()).map(|o| o.to_string());
+        }
+    }
+
+    let count = g.sh("rpm -qal 2>/dev/null | wc -l")?;
+    Ok((deviations, count.trim().parse().unwrap_or(0)))
+}
+
+/// Parse installed packages (with conffiles) from `/var/lib/dpkg/status`
+pub fn parse_dpkg_status(content: &str) -> Vec<InstalledPackage> {
+    let mut packages = Vec::new();
+    let mut current: HashMap<&str, &str> = HashMap::new();
+    let mut conffiles = Vec::new();
+    let mut in_conffiles = false;
+
+    for line in content.lines().chain(std::iter::once("")) {
+        if line.is_empty() {
+            let installed = current
+                .get("Status")
+                .map(|s| s.ends_with(" installed"))
+                .unwrap_or(false);
+            if let (true, Some(name), Some(version)) =
+                (installed, current.get("Package"), current.get("Version"))
+            {
+                packages.push(InstalledPackage {
+                    name: name.to_string(),
+                    version: version.to_string(),
+                    arch: current.get("Architecture").unwrap_or(&"").to_string(),
+                    conffiles: std::mem::take(&mut conffiles),
+                });
+            }
+            current.clear();
+            conffiles.clear();
+            in_conffiles = false;
+        } else if line.starts_with(' ') {
+            if in_conffiles {
+                let fields: Vec<&str> = line.split_whitespace().collect();
+                // "obsolete" conffiles are no longer shipped by the package
+                if fields.len() == 2 {
+                    conffiles.push((fields[0].to_string(), fields[1].to_string()));
+                }
+            }
+        } else if let Some((key, value)) = line.split_once(':') {
+            in_conffiles = key == "Conffiles";
+            current.insert(key, value.trim());
+        }
+    }
+
+    packages
+}
+
+/// Parse `rpm -qa` output in NAME\tEPOCHNUM\tVERSION\tRELEASE\tARCH form
+pub fn parse_rpm_query(output: &str) -> Vec<InstalledPackage> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            if fields.len() != 5 {
+                return None;
+            }
+            // Imported signing keys appear as pseudo-packages
+            if fields[0] == "gpg-pubkey" {
+                return None;
+            }
+            Some(InstalledPackage {
+                name: fields[0].to_string(),
+                version: rpm_evr(fields[1], fields[2], fields[3]),
+                arch: fields[4].to_string(),
+                conffiles: Vec::new(),
+            })
+        })
+        .collect()
+}
+
+/// Parse `md5sum -c --quiet` failures prefixed with `package\t`
+pub fn parse_md5sum_failures(output: &str) -> Vec<FileDeviation> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (package, rest) = line.split_once('\t')?;
+            let (path, status) = rest.rsplit_once(": ")?;
+            if !status.starts_with("FAILED") {
+                return None;
+            }
+            Some(FileDeviation {
+                path: format!("/{}", path.trim_start_matches('/')),
+                // Multi-arch packages have md5sums named package:arch
+                package: Some(package.split(':').next().unwrap_or(package).to_string()),
+                modified: status == "FAILED",
+                config: false,
+            })
+        })
+        .collect()
+}
+
+/// Parse `md5sum` output into path -> checksum
+fn parse_md5sum_output(output: &str) -> HashMap<&str, &str> {
+    output
+        .lines()
+        .filter_map(|line| line.split_once("  "))
+        .map(|(md5, path)| (path, md5))
+        .collect()
+}
+
+/// Parse `rpm -V` output, keeping missing files and content changes
+///
+/// Lines look like `S.5....T.  c /etc/ssh/sshd_config` or
+/// `missing     /usr/bin/foo`. Attribute-only changes (mode, owner,
+/// mtime) are ignored.
+pub fn parse_rpm_verify(output: &str) -> Vec<FileDeviation> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (flags, rest) = line.split_once(' ')?;
+            let rest = rest.trim_start();
+            let (attr, path) = match rest.split_once(' ') {
+                Some((attr, path)) if attr.len() == 1 && !attr.starts_with('/') => {
+                    (attr, path.trim_start())
+                }
+                _ => ("", rest),
+            };
+            if !path.starts_with('/') {
+                return None;
+            }
+
+            let missing = flags == "missing";
+            let modified =
+                !missing && flags.len() == 9 && (flags.contains('5') || flags.contains('S'));
+            if !missing && !modified {
+                return None;
+            }
+
+            Some(FileDeviation {
+                path: path.to_string(),
+                package: None,
+                modified,
+                config: attr == "c",
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_dpkg_status() {
+        let status = "\
+Package: openssh-server
+Status: install ok installed
+Architecture: amd64
+Version: 1:9.2p1-2+deb12u2
+Conffiles:
+ /etc/ssh/moduli 6a2cf8b9e5d7f0b6b7d0d0f1b1bbf1c1
+ /etc/init/ssh.conf 1b2c3d obsolete
+Description: secure shell server
+
+Package: removed-pkg
+Status: deinstall ok config-files
+Version: 1.0
+
+Package: bash
+Status: install ok installed
+Architecture: amd64
+Version: 5.2.15-2+b2
+";
+        let packages = parse_dpkg_status(status);
+
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[0].name, "openssh-server");
+        assert_eq!(packages[0].version, "1:9.2p1-2+deb12u2");
+        assert_eq!(
+            packages[0].conffiles,
+            vec![(
+                "/etc/ssh/moduli".to_string(),
+                "6a2cf8b9e5d7f0b6b7d0d0f1b1bbf1c1".to_string()
+            )]
+        );
+        assert_eq!(packages[1].name, "bash");
+        assert!(packages[1].conffiles.is_empty());
+    }
+
+    #[test]
+    fn test_parse_rpm_query() {
+        let output = "bash\t0\t5.2.26\t3.fc40\tx86_64\n\
+                      gpg-pubkey\t0\ta15b79cc\t63d04c2c\t(none)\n\
+                      openssl\t1\t3.2.1\t2.fc40\tx86_64\n";
+        let packages = parse_rpm_query(output);
+
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[0].version, "5.2.26-3.fc40");
+        assert_eq!(packages[1].version, "1:3.2.1-2.fc40");
+    }
+
+    #[test]
+    fn test_parse_md5sum_failures() {
+        let output = "coreutils\tusr/bin/ls: FAILED\n\
+                      libc6:amd64\tusr/lib/x86_64-linux-gnu/libc.so.6: FAILED open or read\n";
+        let deviations = parse_md5sum_failures(output);
+
+        assert_eq!(deviations.len(), 2);
+        assert_eq!(deviations[0].path, "/usr/bin/ls");
+        assert!(deviations[0].modified);
+        assert_eq!(deviations[1].package.as_deref(), Some("libc6"));
+        assert!(!deviations[1].modified);
+    }
+
+    #[test]
+    fn test_parse_rpm_verify() {
+        let output = "\
+S.5....T.  c /etc/ssh/sshd_config
+.M.......    /usr/bin/ping
+missing   d /usr/share/doc/bash/README
+..5......    /usr/bin/ls
+";
+        let deviations = parse_rpm_verify(output);
+
+        assert_eq!(deviations.len(), 3);
+        assert_eq!(deviations[0].path, "/etc/ssh/sshd_config");
+        assert!(deviations[0].config && deviations[0].modified);
+        assert_eq!(deviations[1].path, "/usr/share/doc/bash/README");
+        assert!(!deviations[1].modified);
+        assert_eq!(deviations[2].path, "/usr/bin/ls");
+        assert!(!deviations[2].config);
+    }
+}
@@ -466,7 +466,7 @@ impl HardeningProfile {
             // Check for users without home directories
             let homeless_users: Vec<_> = users.iter()
                 .filter(|u| u.home.is_empty() || u.home == "/nonexistent")
-                .filter(|u| !u.shell.contains("nologin") && !u.shell.contains("false"))
+                .filter(|u| !u.disabled && !u.shell.contains("nologin") && !u.shell.contains("false"))
                 .collect();
 
             if !homeless_users.is_empty() {
@@ -3,6 +3,7 @@
 
 use super::{Finding, FindingStatus, InspectionProfile, ProfileReport, ReportSection, RiskLevel};
 use anyhow::Result;
+use guestkit::guestfs::UserAccount;
 use guestkit::Guestfs;
 
 pub struct SecurityProfile;
@@ -130,47 +131,53 @@ impl SecurityProfile {
         let mut findings = Vec::new();
 
         if let Ok(users) = g.inspect_users(root) {
-            // Count users with UID 0 (root equivalents)
-            let root_users: Vec<_> = users.iter().filter(|u| u.uid == "0").collect();
-
-            if root_users.len() > 1 {
-                findings.push(Finding {
-                    item: "Root-Equivalent Users".to_string(),
-                    status: FindingStatus::Warning,
-                    message: format!(
-                        "{} users with UID 0: {}",
-                        root_users.len(),
-                        root_users
-                            .iter()
-                            .map(|u| &u.username)
-                            .cloned()
-                            .collect::<Vec<_>>()
-                            .join(", ")
-                    ),
-                    risk_level: Some(RiskLevel::High),
-                });
+            if g.inspect_get_type(root)
+                .is_ok_and(|os_type| os_type == "windows")
+            {
+                self.audit_windows_accounts(&users, &mut findings);
             } else {
+                // Count users with UID 0 (root equivalents)
+                let root_users: Vec<_> = users.iter().filter(|u| u.uid == "0").collect();
+
+                if root_users.len() > 1 {
+                    findings.push(Finding {
+                        item: "Root-Equivalent Users".to_string(),
+                        status: FindingStatus::Warning,
+                        message: format!(
+                            "{} users with UID 0: {}",
+                            root_users.len(),
+                            root_users
+                                .iter()
+                                .map(|u| &u.username)
+                                .cloned()
+                                .collect::<Vec<_>>()
+                                .join(", ")
+                        ),
+                        risk_level: Some(RiskLevel::High),
+                    });
+                } else {
+                    findings.push(Finding {
+                        item: "Root-Equivalent Users".to_string(),
+                        status: FindingStatus::Pass,
+                        message: "Only 'root' user has UID 0".to_string(),
+                        risk_level: Some(RiskLevel::Low),
+                    });
+                }
+
+                // Check for users with no password (empty shell)
+                let no_password_users = users
+                    .iter()
+                    .filter(|u| u.shell.contains("nologin") || u.shell.contains("false"))
+                    .count();
+
                 findings.push(Finding {
-                    item: "Root-Equivalent Users".to_string(),
-                    status: FindingStatus::Pass,
-                    message: "Only 'root' user has UID 0".to_string(),
-                    risk_level: Some(RiskLevel::Low),
+                    item: "System Users".to_string(),
+                    status: FindingStatus::Info,
+                    message: format!("{} users with disabled login", no_password_users),
+                    risk_level: None,
                 });
             }
 
-            // Check for users with no password (empty shell)
-            let no_password_users = users
-                .iter()
-                .filter(|u| u.shell.contains("nologin") || u.shell.contains("false"))
-                .count();
-
-            findings.push(Finding {
-                item: "System Users".to_string(),
-                status: FindingStatus::Info,
-                message: format!("{} users with disabled login", no_password_users),
-                risk_level: None,
-            });
-
             // Total user count
             findings.push(Finding {
                 item: "Total User Accounts".to_string(),
@@ -193,6 +200,68 @@ impl SecurityProfile {
         }
     }
 
+    /// Local account checks for Windows, where administrators are the
+    /// members of the Administrators group rather than UID 0
+    fn audit_windows_accounts(&self, users: &[UserAccount], findings: &mut Vec<Finding>) {
+        let admins: Vec<_> = users
+            .iter()
+            .filter(|u| !u.disabled && u.groups.iter().any(|g| g == "Administrators"))
+            .map(|u| u.username.as_str())
+            .collect();
+
+        findings.push(Finding {
+            item: "Administrators".to_string(),
+            status: if admins.len() > 1 {
+                FindingStatus::Warning
+            } else {
+                FindingStatus::Info
+            },
+            message: format!(
+                "{} enabled administrator(s): {}",
+                admins.len(),
+                admins.join(", ")
+            ),
+            risk_level: if admins.len() > 1 {
+                Some(RiskLevel::Medium)
+            } else {
+                None
+            },
+        });
+
+        // Well-known RIDs of the built-in accounts
+        for (rid, item, risk) in [
+            ("500", "Built-in Administrator", RiskLevel::Medium),
+            ("501", "Guest Account", RiskLevel::High),
+        ] {
+            let Some(user) = users.iter().find(|u| u.uid == rid) else {
+                continue;
+            };
+            findings.push(if user.disabled {
+                Finding {
+                    item: item.to_string(),
+                    status: FindingStatus::Pass,
+                    message: format!("'{}' is disabled", user.username),
+                    risk_level: Some(RiskLevel::Low),
+                }
+            } else {
+                Finding {
+                    item: item.to_string(),
+                    status: FindingStatus::Warning,
+                    message: format!("'{}' is enabled", user.username),
+                    risk_level: Some(risk),
+                }
+            });
+        }
+
+        let disabled = users.iter().filter(|u| u.disabled).count();
+        findings.push(Finding {
+            item: "Disabled Accounts".to_string(),
+            status: FindingStatus::Info,
+            message: format!("{} disabled accounts", disabled),
+            risk_level: None,
+        });
+    }
+
     fn audit_firewall(&self, g: &mut Guestfs, root: &str) -> ReportSection {
         let mut findings = Vec::new();
 
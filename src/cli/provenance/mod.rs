@@ -0,0 +1,887 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! "What changed since the base image" provenance reports
+//!
+//! Compares an image against its base (the qcow2 backing file, another image
+//! or a recorded baseline) and lists every added, modified and removed file
+//! together with the package that owns it and the action that most likely
+//! produced the change.
+
+pub mod reporter;
+
+use crate::cli::plain;
+use crate::cli::pristine::verify;
+use crate::cli::target;
+use anyhow::{Context, Result};
+use guestkit::Guestfs;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::path::Path;
+
+/// Guest trees that are never compared (pseudo and volatile filesystems)
+const SKIPPED_PREFIXES: &[&str] = &["/proc/", "/sys/", "/dev/", "/run/", "/tmp/", "/var/tmp/"];
+
+/// Paths that the system rewrites on its own
+const GENERATED_PATHS: &[&str] = &[
+    "/var/log/",
+    "/var/cache/",
+    "/var/lib/apt/",
+    "/var/lib/dpkg/",
+    "/var/lib/rpm/",
+    "/var/lib/dnf/",
+    "/var/lib/yum/",
+    "/var/lib/pacman/",
+    "/var/lib/systemd/",
+    "/var/lib/cloud/",
+    "/etc/machine-id",
+    "/etc/ld.so.cache",
+    "/etc/ssh/ssh_host_",
+    "/etc/adjtime",
+    "/etc/resolv.conf",
+];
+
+/// Unpackaged locations that are normally edited by hand
+const ADMIN_PATHS: &[&str] = &["/etc/", "/root/", "/home/", "/usr/local/", "/opt/", "/srv/"];
+
+/// Header markers left by configuration management tools, matched lowercase
+const CONFIG_MANAGEMENT_MARKERS: &[(&str, &str)] = &[
+    ("ansible managed", "Ansible"),
+    ("managed by ansible", "Ansible"),
+    ("managed by puppet", "Puppet"),
+    ("autogenerated at", "Puppet"),
+    ("generated by chef", "Chef"),
+    ("managed by chef", "Chef"),
+    ("managed by salt", "Salt"),
+    ("salt managed", "Salt"),
+];
+
+/// Largest file searched for configuration management markers
+const MARKER_SCAN_LIMIT: i64 = 64 * 1024;
+
+/// State of a regular file, enough to tell whether it changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileState {
+    pub size: i64,
+    pub mode: u32,
+    pub mtime: i64,
+}
+
+/// Files and installed packages of one image
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Snapshot {
+    pub package_format: String,
+    /// Installed package name to version
+    pub packages: BTreeMap<String, String>,
+    pub files: BTreeMap<String, FileState>,
+}
+
+/// Snapshot recorded with `--record`, usable later as `--baseline`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Baseline {
+    pub image_path: String,
+    pub recorded_at: String,
+    #[serde(flatten)]
+    pub snapshot: Snapshot,
+}
+
+impl Baseline {
+    /// Load a recorded baseline
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("reading baseline {}", path.display()))?;
+        serde_json::from_str(&content)
+            .with_context(|| format!("parsing baseline {}", path.display()))
+    }
+}
+
+/// What the report was compared against
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum BaseKind {
+    /// The image's qcow2 backing file
+    BackingFile,
+    /// An image named on the command line
+    Image,
+    /// A baseline recorded with `--record`
+    Baseline,
+}
+
+impl BaseKind {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::BackingFile => "backing file",
+            Self::Image => "image",
+            Self::Baseline => "recorded baseline",
+        }
+    }
+}
+
+/// How a file differs from the base
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ChangeKind {
+    Added,
+    Modified,
+    Removed,
+}
+
+impl ChangeKind {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Added => "added",
+            Self::Modified => "modified",
+            Self::Removed => "removed",
+        }
+    }
+}
+
+/// Most likely cause of a file change
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum Action {
+    PackageInstall,
+    PackageUpgrade,
+    PackageRemoval,
+    ConfigManagement,
+    ManualEdit,
+    SystemGenerated,
+    Unknown,
+}
+
+impl Action {
+    pub const ALL: [Action; 7] = [
+        Self::PackageInstall,
+        Self::PackageUpgrade,
+        Self::PackageRemoval,
+        Self::ConfigManagement,
+        Self::ManualEdit,
+        Self::SystemGenerated,
+        Self::Unknown,
+    ];
+
+    pub fn emoji(&self) -> &str {
+        match self {
+            Self::PackageInstall => "📦",
+            Self::PackageUpgrade => "⬆️",
+            Self::PackageRemoval => "🗑️",
+            Self::ConfigManagement => "🤖",
+            Self::ManualEdit => "✍️",
+            Self::SystemGenerated => "⚙️",
+            Self::Unknown => "❓",
+        }
+    }
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::PackageInstall => "package-install",
+            Self::PackageUpgrade => "package-upgrade",
+            Self::PackageRemoval => "package-removal",
+            Self::ConfigManagement => "config-management",
+            Self::ManualEdit => "manual-edit",
+            Self::SystemGenerated => "system-generated",
+            Self::Unknown => "unknown",
+        }
+    }
+}
+
+/// Package installed, removed or changed since the base
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct PackageChange {
+    pub name: String,
+    pub change: PackageChangeKind,
+    pub old_version: Option<String>,
+    pub new_version: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum PackageChangeKind {
+    Installed,
+    Removed,
+    /// Version differs from the base (upgrade or downgrade)
+    Changed,
+}
+
+/// File that differs from the base
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct FileChange {
+    pub path: String,
+    pub change: ChangeKind,
+    /// Owning package, when the file is packaged
+    pub package: Option<String>,
+    pub action: Action,
+    /// Why the action was chosen
+    pub evidence: String,
+    /// Size in the image (in the base for removed files)
+    pub size: i64,
+    /// Modification time in the image (in the base for removed files)
+    pub modified_at: Option<String>,
+}
+
+/// Provenance summary
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ProvenanceSummary {
+    pub files_compared: usize,
+    pub added: usize,
+    pub modified: usize,
+    pub removed: usize,
+    pub packages_installed: usize,
+    pub packages_removed: usize,
+    pub packages_changed: usize,
+    /// Changed files per action
+    pub by_action: BTreeMap<String, usize>,
+}
+
+/// Provenance report
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ProvenanceReport {
+    pub image_path: String,
+    pub base: String,
+    pub base_kind: BaseKind,
+    pub generated_at: String,
+    /// Guest directories that were compared
+    pub paths: Vec<String>,
+    pub package_changes: Vec<PackageChange>,
+    pub files: Vec<FileChange>,
+    pub summary: ProvenanceSummary,
+    pub warnings: Vec<String>,
+}
+
+/// Base to compare an image against
+#[derive(Debug, Clone)]
+pub enum Base {
+    /// The image's own backing file
+    BackingFile,
+    /// Another disk image
+    Image(String),
+    /// A baseline recorded earlier
+    Baseline(String),
+}
+
+/// Options for a provenance report
+#[derive(Debug, Clone, Default)]
+pub struct ProvenanceOptions {
+    /// Guest directories to compare (`/` if empty)
+    pub paths: Vec<String>,
+    pub verbose: bool,
+}
+
+/// Guest image opened read-only with its filesystems mounted
+struct Opened {
+    g: Guestfs,
+    package_format: String,
+}
+
+fn open_image(image: &str, verbose: bool) -> Result<Opened> {
+    let mut g = Guestfs::new()?;
+    g.set_verbose(verbose);
+    g.add_drive_ro(image)?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
+    g.launch()?;
+
+    let roots = g.inspect_os()?;
+    if roots.is_empty() {
+        anyhow::bail!("No operating systems found in disk image: {}", image);
+    }
+
+    let root = &roots[0];
+    let package_format = g
+        .inspect_get_package_format(root)
+        .unwrap_or_else(|_| "unknown".to_string());
+
+    // Mount parents before children
+    g.mount_os_ro(root)?;
+
+    Ok(Opened { g, package_format })
+}
+
+/// Record the files and packages of a mounted image
+fn snapshot(opened: &mut Opened, paths: &[String], warnings: &mut Vec<String>) -> Snapshot {
+    let g = &mut opened.g;
+
+    let mut packages = BTreeMap::new();
+    match verify::installed_packages(g, &opened.package_format) {
+        Ok(installed) => {
+            for package in installed {
+                packages.insert(package.name, package.version);
+            }
+        }
+        Err(e) => warnings.push(format!("installed packages: {}", e)),
+    }
+
+    let mut files = BTreeMap::new();
+    for dir in paths {
+        let found = match g.find(dir) {
+            Ok(found) => found,
+            Err(e) => {
+                warnings.push(format!("{}: {}", dir, e));
+                continue;
+            }
+        };
+        for path in found {
+            let path = if dir == "/" {
+                path
+            } else {
+                format!("{}{}", dir.trim_end_matches('/'), path)
+            };
+            if path.is_empty() || is_skipped(&path) {
+                continue;
+            }
+            if let Ok(stat) = g.lstat(&path) {
+                files.insert(
+                    path,
+                    FileState {
+                        size: stat.size,
+                        mode: stat.mode,
+                        mtime: stat.mtime,
+                    },
+                );
+            }
+        }
+    }
+
+    Snapshot {
+        package_format: opened.package_format.clone(),
+        packages,
+        files,
+    }
+}
+
+fn is_skipped(path: &str) -> bool {
+    SKIPPED_PREFIXES
+        .iter()
+        .any(|prefix| path.starts_with(prefix))
+}
+
+fn compare_paths(options: &ProvenanceOptions) -> Vec<String> {
+    if options.paths.is_empty() {
+        vec!["/".to_string()]
+    } else {
+        options.paths.clone()
+    }
+}
+
+/// Record a baseline of an image for later comparisons
+pub fn record_baseline<P: AsRef<Path>>(
+    image_path: P,
+    options: &ProvenanceOptions,
+) -> Result<Baseline> {
+    let image_path_str = image_path.as_ref().display().to_string();
+
+    if options.verbose {
+        println!("{}Recording baseline: {}", plain::text("📸 "), image_path_str);
+    }
+
+    let mut opened = open_image(&image_path_str, options.verbose)?;
+    let mut warnings = Vec::new();
+    let snapshot = snapshot(&mut opened, &compare_paths(options), &mut warnings);
+    opened.g.shutdown()?;
+
+    for warning in &warnings {
+        eprintln!("{}  {}", plain::text("⚠️"), warning);
+    }
+
+    Ok(Baseline {
+        image_path: image_path_str,
+        recorded_at: chrono::Utc::now().to_rfc3339(),
+        snapshot,
+    })
+}
+
+/// Build a provenance report for an image against its base
+pub fn scan_provenance<P: AsRef<Path>>(
+    image_path: P,
+    base: &Base,
+    options: &ProvenanceOptions,
+) -> Result<ProvenanceReport> {
+    let image_path_str = image_path.as_ref().display().to_string();
+    let paths = compare_paths(options);
+    let mut warnings = Vec::new();
+
+    if options.verbose {
+        println!("{}Tracing changes in: {}", plain::text("🔍 "), image_path_str);
+    }
+
+    // Resolve the base before opening anything
+    let (base_name, base_kind) = match base {
+        Base::BackingFile => {
+            let mut g = Guestfs::new()?;
+            let backing = g.disk_backing_file(&image_path_str)?.ok_or_else(|| {
+                anyhow::anyhow!(
+                    "{} has no backing file; use --base or --baseline",
+                    image_path_str
+                )
+            })?;
+            (backing, BaseKind::BackingFile)
+        }
+        Base::Image(path) => (path.clone(), BaseKind::Image),
+        Base::Baseline(path) => (path.clone(), BaseKind::Baseline),
+    };
+
+    let mut current = open_image(&image_path_str, options.verbose)?;
+    let mut base_image = match base_kind {
+        BaseKind::Baseline => None,
+        _ => {
+            if options.verbose {
+                println!("{}Opening base: {}", plain::text("📀 "), base_name);
+            }
+            Some(open_image(&base_name, options.verbose)?)
+        }
+    };
+
+    if options.verbose {
+        println!("{}Walking {}", plain::text("📁 "), paths.join(", "));
+    }
+    let after = snapshot(&mut current, &paths, &mut warnings);
+    let before = match base_image.as_mut() {
+        Some(opened) => snapshot(opened, &paths, &mut warnings),
+        None => Baseline::load(Path::new(&base_name))?.snapshot,
+    };
+
+    // Files whose size and mode match but whose mtime moved are compared by
+    // content when both images are at hand
+    let mut touched = Vec::new();
+    let mut changes = diff_files(&before.files, &after.files, |path| {
+        touched.push(path.to_string());
+        true
+    });
+    if let Some(opened) = base_image.as_mut() {
+        let unchanged: HashSet<String> = touched
+            .into_iter()
+            .filter(|path| {
+                let before = opened.g.checksum("sha256", path).ok();
+                let after = current.g.checksum("sha256", path).ok();
+                before.is_some() && before == after
+            })
+            .collect();
+        changes.retain(|(path, _)| !unchanged.contains(path));
+    }
+
+    let package_changes = diff_packages(&before.packages, &after.packages);
+
+    if options.verbose {
+        println!("{}Attributing {} changed files to packages", plain::text("📦 "), changes.len());
+    }
+
+    let mut owners = match current.g.package_file_owners() {
+        Ok(owners) => owners,
+        Err(e) => {
+            warnings.push(format!("package file owners: {}", e));
+            HashMap::new()
+        }
+    };
+    let base_owners = match base_image.as_mut() {
+        Some(opened) => opened.g.package_file_owners().unwrap_or_default(),
+        None => HashMap::new(),
+    };
+
+    let package_delta: HashMap<&str, PackageChangeKind> = package_changes
+        .iter()
+        .map(|change| (change.name.as_str(), change.change))
+        .collect();
+
+    let mut files = Vec::new();
+    for (path, change) in changes {
+        let (package, state) = match change {
+            ChangeKind::Removed => (
+                base_owners.get(&path).cloned(),
+                before.files.get(&path).copied(),
+            ),
+            _ => (owners.remove(&path), after.files.get(&path).copied()),
+        };
+
+        let marker = match (change, state) {
+            (ChangeKind::Removed, _) => None,
+            (_, Some(state)) if state.size <= MARKER_SCAN_LIMIT && path.starts_with("/etc/") => {
+                current
+                    .g
+                    .read_file(&path)
+                    .ok()
+                    .and_then(|content| config_management_tool(&String::from_utf8_lossy(&content)))
+            }
+            _ => None,
+        };
+
+        let (action, evidence) =
+            classify_change(change, &path, package.as_deref(), &package_delta, marker);
+        files.push(FileChange {
+            path,
+            change,
+            package,
+            action,
+            evidence,
+            size: state.map(|s| s.size).unwrap_or(0),
+            modified_at: state
+                .and_then(|s| chrono::DateTime::from_timestamp(s.mtime, 0).map(|t| t.to_rfc3339())),
+        });
+    }
+
+    current.g.shutdown()?;
+    if let Some(mut opened) = base_image {
+        opened.g.shutdown()?;
+    }
+
+    let summary = summarize(after.files.len(), &files, &package_changes);
+
+    Ok(ProvenanceReport {
+        image_path: image_path_str,
+        base: base_name,
+        base_kind,
+        generated_at: chrono::Utc::now().to_rfc3339(),
+        paths,
+        package_changes,
+        files,
+        summary,
+        warnings,
+    })
+}
+
+/// Compare two file listings
+///
+/// A file whose size and mode are unchanged but whose mtime moved is passed
+/// to `touched`, which decides whether to report it as modified.
+pub fn diff_files(
+    before: &BTreeMap<String, FileState>,
+    after: &BTreeMap<String, FileState>,
+    mut touched: impl FnMut(&str) -> bool,
+) -> Vec<(String, ChangeKind)> {
+    let mut changes = Vec::new();
+
+    for (path, state) in after {
+        match before.get(path) {
+            None => changes.push((path.clone(), ChangeKind::Added)),
+            Some(old) if old.size != state.size || old.mode != state.mode => {
+                changes.push((path.clone(), ChangeKind::Modified))
+            }
+            Some(old) if old.mtime != state.mtime && touched(path) => {
+                changes.push((path.clone(), ChangeKind::Modified))
+            }
+            Some(_) => {}
+        }
+    }
+
+    for path in before.keys() {
+        if !after.contains_key(path) {
+            changes.push((path.clone(), ChangeKind::Removed));
+        }
+    }
+
+    changes.sort_by(|a, b| a.0.cmp(&b.0));
+    changes
+}
+
+/// Compare two package sets
+pub fn diff_packages(
+    before: &BTreeMap<String, String>,
+    after: &BTreeMap<String, String>,
+) -> Vec<PackageChange> {
+    let mut changes = Vec::new();
+
+    for (name, version) in after {
+        match before.get(name) {
+            None => changes.push(PackageChange {
+                name: name.clone(),
+                change: PackageChangeKind::Installed,
+                old_version: None,
+                new_version: Some(version.clone()),
+            }),
+            Some(old) if old != version => changes.push(PackageChange {
+                name: name.clone(),
+                change: PackageChangeKind::Changed,
+                old_version: Some(old.clone()),
+                new_version: Some(version.clone()),
+            }),
+            Some(_) => {}
+        }
+    }
+
+    for (name, version) in before {
+        if !after.contains_key(name) {
+            changes.push(PackageChange {
+                name: name.clone(),
+                change: PackageChangeKind::Removed,
+                old_version: Some(version.clone()),
+                new_version: None,
+            });
+        }
+    }
+
+    changes.sort_by(|a, b| a.name.cmp(&b.name));
+    changes
+}
+
+/// Configuration management tool named in a file's header
+pub fn config_management_tool(content: &str) -> Option<&'static str> {
+    let header: String = content.lines().take(20).collect::<Vec<_>>().join("\n");
+    let header = header.to_lowercase();
+    CONFIG_MANAGEMENT_MARKERS
+        .iter()
+        .find(|(marker, _)| header.contains(marker))
+        .map(|(_, tool)| *tool)
+}
+
+/// Decide which action most likely produced a file change
+///
+/// Configuration management markers win, then the owning package's own
+/// change, then well-known generated paths. Remaining changes to packaged
+/// files and to admin-owned directories are taken as manual edits.
+pub fn classify_change(
+    change: ChangeKind,
+    path: &str,
+    package: Option<&str>,
+    package_delta: &HashMap<&str, PackageChangeKind>,
+    config_tool: Option<&str>,
+) -> (Action, String) {
+    if let Some(tool) = config_tool {
+        return (Action::ConfigManagement, format!("{} header in file", tool));
+    }
+
+    if let Some(package) = package {
+        match (package_delta.get(package), change) {
+            (Some(PackageChangeKind::Installed), _) => {
+                return (
+                    Action::PackageInstall,
+                    format!("{} installed since base", package),
+                )
+            }
+            (Some(PackageChangeKind::Changed), _) => {
+                return (
+                    Action::PackageUpgrade,
+                    format!("{} changed version since base", package),
+                )
+            }
+            (Some(PackageChangeKind::Removed), _) => {
+                return (
+                    Action::PackageRemoval,
+                    format!("{} removed since base", package),
+                )
+            }
+            (None, ChangeKind::Modified) => {
+                return (
+                    Action::ManualEdit,
+                    format!("{} unchanged but its file differs", package),
+                )
+            }
+            (None, ChangeKind::Removed) => {
+                return (
+                    Action::ManualEdit,
+                    format!("{} unchanged but its file is gone", package),
+                )
+            }
+            (None, ChangeKind::Added) => {}
+        }
+    }
+
+    if GENERATED_PATHS
+        .iter()
+        .any(|prefix| path.starts_with(prefix))
+    {
+        return (
+            Action::SystemGenerated,
+            "written by the system at runtime".to_string(),
+        );
+    }
+
+    if let Some(package) = package {
+        return (
+            Action::Unknown,
+            format!("listed by unchanged package {}", package),
+        );
+    }
+
+    if ADMIN_PATHS.iter().any(|prefix| path.starts_with(prefix)) {
+        return (
+            Action::ManualEdit,
+            "unpackaged file in an admin-managed directory".to_string(),
+        );
+    }
+
+    (Action::Unknown, "unpackaged file".to_string())
+}
+
+/// Build summary statistics for a provenance report
+pub fn summarize(
+    files_compared: usize,
+    files: &[FileChange],
+    packages: &[PackageChange],
+) -> ProvenanceSummary {
+    let count_files = |kind: ChangeKind| files.iter().filter(|f| f.change == kind).count();
+    let count_packages =
+        |kind: PackageChangeKind| packages.iter().filter(|p| p.change == kind).count();
+
+    let mut by_action = BTreeMap::new();
+    for file in files {
+        *by_action
+            .entry(file.action.as_str().to_string())
+            .or_insert(0) += 1;
+    }
+
+    ProvenanceSummary {
+        files_compared,
+        added: count_files(ChangeKind::Added),
+        modified: count_files(ChangeKind::Modified),
+        removed: count_files(ChangeKind::Removed),
+        packages_installed: count_packages(PackageChangeKind::Installed),
+        packages_removed: count_packages(PackageChangeKind::Removed),
+        packages_changed: count_packages(PackageChangeKind::Changed),
+        by_action,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn state(size: i64, mtime: i64) -> FileState {
+        FileState {
+            size,
+            mode: 0o100644,
+            mtime,
+        }
+    }
+
+    #[test]
+    fn test_diff_files() {
+        let before = BTreeMap::from([
+            ("/etc/hosts".to_string(), state(100, 1)),
+            ("/etc/motd".to_string(), state(10, 1)),
+            ("/etc/issue".to_string(), state(20, 1)),
+            ("/usr/bin/old".to_string(), state(500, 1)),
+        ]);
+        let after = BTreeMap::from([
+            ("/etc/hosts".to_string(), state(120, 2)),
+            ("/etc/motd".to_string(), state(10, 5)),
+            ("/etc/issue".to_string(), state(20, 1)),
+            ("/opt/app/run.sh".to_string(), state(50, 3)),
+        ]);
+
+        let mut touched = Vec::new();
+        let changes = diff_files(&before, &after, |path| {
+            touched.push(path.to_string());
+            false
+        });
+
+        assert_eq!(touched, vec!["/etc/motd".to_string()]);
+        assert_eq!(
+            changes,
+            vec![
+                ("/etc/hosts".to_string(), ChangeKind::Modified),
+                ("/opt/app/run.sh".to_string(), ChangeKind::Added),
+                ("/usr/bin/old".to_string(), ChangeKind::Removed),
+            ]
+        );
+    }
+
+    #[test]
+    fn test_diff_packages() {
+        let before = BTreeMap::from([
+            ("bash".to_string(), "5.2".to_string()),
+            ("openssl".to_string(), "3.0.11".to_string()),
+            ("telnet".to_string(), "0.17".to_string()),
+        ]);
+        let after = BTreeMap::from([
+            ("bash".to_string(), "5.2".to_string()),
+            ("nginx".to_string(), "1.24".to_string()),
+            ("openssl".to_string(), "3.0.13".to_string()),
+        ]);
+
+        let changes = diff_packages(&before, &after);
+        let kinds: Vec<_> = changes
+            .iter()
+            .map(|c| (c.name.as_str(), c.change))
+            .collect();
+        assert_eq!(
+            kinds,
+            vec![
+                ("nginx", PackageChangeKind::Installed),
+                ("openssl", PackageChangeKind::Changed),
+                ("telnet", PackageChangeKind::Removed),
+            ]
+        );
+        assert_eq!(changes[1].old_version.as_deref(), Some("3.0.11"));
+
+        let summary = summarize(10, &[], &changes);
+        assert_eq!(summary.packages_installed, 1);
+        assert_eq!(summary.packages_changed, 1);
+        assert_eq!(summary.packages_removed, 1);
+    }
+
+    #[test]
+    fn test_classify_change() {
+        let delta = HashMap::from([
+            ("nginx", PackageChangeKind::Installed),
+            ("openssl", PackageChangeKind::Changed),
+            ("telnet", PackageChangeKind::Removed),
+        ]);
+        let action =
+            |change, path, package, tool| classify_change(change, path, package, &delta, tool).0;
+
+        assert_eq!(
+            action(ChangeKind::Added, "/usr/sbin/nginx", Some("nginx"), None),
+            Action::PackageInstall
+        );
+        assert_eq!(
+            action(
+                ChangeKind::Modified,
+                "/usr/lib/libssl.so.3",
+                Some("openssl"),
+                None
+            ),
+            Action::PackageUpgrade
+        );
+        assert_eq!(
+            action(ChangeKind::Removed, "/usr/bin/telnet", Some("telnet"), None),
+            Action::PackageRemoval
+        );
+        assert_eq!(
+            action(
+                ChangeKind::Modified,
+                "/etc/ssh/sshd_config",
+                Some("openssh-server"),
+                None
+            ),
+            Action::ManualEdit
+        );
+        assert_eq!(
+            action(
+                ChangeKind::Modified,
+                "/etc/ssh/sshd_config",
+                Some("openssh-server"),
+                Some("Ansible")
+            ),
+            Action::ConfigManagement
+        );
+        assert_eq!(
+            action(ChangeKind::Modified, "/var/log/syslog", None, None),
+            Action::SystemGenerated
+        );
+        assert_eq!(
+            action(ChangeKind::Added, "/etc/sudoers.d/deploy", None, None),
+            Action::ManualEdit
+        );
+        assert_eq!(
+            action(ChangeKind::Added, "/usr/lib/python3/foo.pyc", None, None),
+            Action::Unknown
+        );
+    }
+
+    #[test]
+    fn test_config_management_tool() {
+        assert_eq!(
+            config_management_tool("# Ansible managed\nPort 22\n"),
+            Some("Ansible")
+        );
+        assert_eq!(
+            config_management_tool("# This file is managed by Puppet. DO NOT EDIT.\n"),
+            Some("Puppet")
+        );
+        assert_eq!(
+            config_management_tool("# Generated by Chef for web-01\n"),
+            Some("Chef")
+        );
+        assert_eq!(
+            config_management_tool("Port 22\nPermitRootLogin no\n"),
+            None
+        );
+    }
+}
@@ -0,0 +1,280 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Provenance report formatting
+
+use super::{Action, PackageChangeKind, ProvenanceReport};
+
+/// Number of files listed per action in the text report
+const MAX_LISTED: usize = 50;
+
+fn package_change_label(change: PackageChangeKind) -> &'static str {
+    match change {
+        PackageChangeKind::Installed => "installed",
+        PackageChangeKind::Removed => "removed",
+        PackageChangeKind::Changed => "changed",
+    }
+}
+
+fn version_span(old: Option<&str>, new: Option<&str>) -> String {
+    match (old, new) {
+        (Some(old), Some(new)) => format!("{} → {}", old, new),
+        (None, Some(new)) => new.to_string(),
+        (Some(old), None) => old.to_string(),
+        (None, None) => String::new(),
+    }
+}
+
+/// Format provenance report as text
+pub fn format_report(report: &ProvenanceReport, verbose: bool) -> String {
+    let mut output = String::new();
+
+    output.push_str("🧬 Provenance Report\n");
+    output.push_str("====================\n\n");
+    output.push_str(&format!("Image: {}\n", report.image_path));
+    output.push_str(&format!(
+        "Base: {} ({})\n",
+        report.base,
+        report.base_kind.as_str()
+    ));
+    output.push_str(&format!("Compared: {}\n", report.paths.join(", ")));
+    output.push_str(&format!("Generated: {}\n\n", report.generated_at));
+
+    // Summary
+    let summary = &report.summary;
+    output.push_str("📊 Summary\n");
+    output.push_str("----------\n");
+    output.push_str(&format!("Files compared: {}\n", summary.files_compared));
+    output.push_str(&format!(
+        "Added: {}, modified: {}, removed: {}\n",
+        summary.added, summary.modified, summary.removed
+    ));
+    output.push_str(&format!(
+        "Packages installed: {}, removed: {}, changed: {}\n",
+        summary.packages_installed, summary.packages_removed, summary.packages_changed
+    ));
+    for action in Action::ALL {
+        if let Some(count) = summary.by_action.get(action.as_str()) {
+            output.push_str(&format!(
+                "{} {}: {}\n",
+                action.emoji(),
+                action.as_str(),
+                count
+            ));
+        }
+    }
+    output.push('\n');
+
+    if !report.package_changes.is_empty() {
+        let heading = format!("📦 Package Changes ({})", report.package_changes.len());
+        output.push_str(&format!("{}\n", heading));
+        output.push_str(&format!("{}\n", "-".repeat(heading.chars().count())));
+        for package in &report.package_changes {
+            output.push_str(&format!(
+                "{} [{}] {}\n",
+                package.name,
+                package_change_label(package.change),
+                version_span(
+                    package.old_version.as_deref(),
+                    package.new_version.as_deref()
+                )
+            ));
+        }
+        output.push('\n');
+    }
+
+    for action in Action::ALL {
+        let files: Vec<_> = report.files.iter().filter(|f| f.action == action).collect();
+        if files.is_empty() {
+            continue;
+        }
+
+        let heading = format!("{} {} ({})", action.emoji(), action.as_str(), files.len());
+        output.push_str(&format!("{}\n", heading));
+        output.push_str(&format!("{}\n", "-".repeat(heading.chars().count())));
+        let limit = if verbose { files.len() } else { MAX_LISTED };
+        for file in files.iter().take(limit) {
+            output.push_str(&format!(
+                "{} [{}] {}\n",
+                file.path,
+                file.change.as_str(),
+                file.package.as_deref().unwrap_or("unowned")
+            ));
+            output.push_str(&format!("   └─ {}\n", file.evidence));
+        }
+        if files.len() > limit {
+            output.push_str(&format!("... and {} more\n", files.len() - limit));
+        }
+        output.push('\n');
+    }
+
+    if report.files.is_empty() {
+        output.push_str("✅ No file changes since base\n\n");
+    }
+
+    if !report.warnings.is_empty() {
+        output.push_str("⚠️  Warnings\n");
+        output.push_str("-----------\n");
+        for warning in &report.warnings {
+            output.push_str(&format!("{}\n", warning));
+        }
+        output.push('\n');
+    }
+
+    output
+}
+
+/// Format changed files and packages as CSV
+pub fn format_csv(report: &ProvenanceReport) -> String {
+    let mut output = String::from("Type,Path,Change,Package,Action,Evidence,Modified\n");
+
+    for package in &report.package_changes {
+        output.push_str(&format!(
+            "package,\"{}\",{},\"{}\",,\"{}\",\n",
+            package.name,
+            package_change_label(package.change),
+            package.name,
+            version_span(
+                package.old_version.as_deref(),
+                package.new_version.as_deref()
+            )
+        ));
+    }
+
+    for file in &report.files {
+        output.push_str(&format!(
+            "file,\"{}\",{},\"{}\",{},\"{}\",{}\n",
+            file.path.replace('"', "\"\""),
+            file.change.as_str(),
+            file.package.as_deref().unwrap_or(""),
+            file.action.as_str(),
+            file.evidence.replace('"', "\"\""),
+            file.modified_at.as_deref().unwrap_or("")
+        ));
+    }
+
+    output
+}
+
+fn escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('"', "&quot;")
+}
+
+/// Format provenance report as a standalone HTML page
+pub fn format_html(report: &ProvenanceReport) -> String {
+    let mut html = String::new();
+
+    html.push_str("<!DOCTYPE html>\n");
+    html.push_str("<html>\n<head>\n");
+    html.push_str("<meta charset=\"utf-8\">\n");
+    html.push_str("<title>Provenance Report</title>\n");
+    html.push_str("<style>\n");
+    html.push_str("body { font-family: Arial, sans-serif; max-width: 1200px; margin: 0 auto; padding: 20px; }\n");
+    html.push_str("h1, h2 { color: #333; }\n");
+    html.push_str(
+        ".info-box { background: #f0f0f0; padding: 15px; margin: 10px 0; border-radius: 5px; }\n",
+    );
+    html.push_str(".added { color: #388e3c; }\n");
+    html.push_str(".modified { color: #f57c00; }\n");
+    html.push_str(".removed { color: #d32f2f; }\n");
+    html.push_str("table { border-collapse: collapse; width: 100%; margin: 10px 0; }\n");
+    html.push_str("th, td { padding: 8px; text-align: left; border-bottom: 1px solid #ddd; }\n");
+    html.push_str("th { background-color: #2196f3; color: white; }\n");
+    html.push_str("code { font-size: 0.9em; }\n");
+    html.push_str("</style>\n");
+    html.push_str("</head>\n<body>\n");
+
+    html.push_str("<h1>🧬 Provenance Report</h1>\n");
+
+    let summary = &report.summary;
+    html.push_str("<div class=\"info-box\">\n");
+    html.push_str(&format!(
+        "<p><strong>Image:</strong> {}</p>\n",
+        escape(&report.image_path)
+    ));
+    html.push_str(&format!(
+        "<p><strong>Base:</strong> {} ({})</p>\n",
+        escape(&report.base),
+        report.base_kind.as_str()
+    ));
+    html.push_str(&format!(
+        "<p><strong>Generated:</strong> {}</p>\n",
+        report.generated_at
+    ));
+    html.push_str(&format!(
+        "<p><strong>Files:</strong> {} compared, {} added, {} modified, {} removed</p>\n",
+        summary.files_compared, summary.added, summary.modified, summary.removed
+    ));
+    html.push_str(&format!(
+        "<p><strong>Packages:</strong> {} installed, {} removed, {} changed</p>\n",
+        summary.packages_installed, summary.packages_removed, summary.packages_changed
+    ));
+    html.push_str("</div>\n");
+
+    if !summary.by_action.is_empty() {
+        html.push_str("<h2>Likely Actions</h2>\n");
+        html.push_str("<table>\n");
+        html.push_str("<tr><th>Action</th><th>Files</th></tr>\n");
+        for action in Action::ALL {
+            if let Some(count) = summary.by_action.get(action.as_str()) {
+                html.push_str(&format!(
+                    "<tr><td>{} {}</td><td>{}</td></tr>\n",
+                    action.emoji(),
+                    action.as_str(),
+                    count
+                ));
+            }
+        }
+        html.push_str("</table>\n");
+    }
+
+    if !report.package_changes.is_empty() {
+        html.push_str("<h2>Package Changes</h2>\n");
+        html.push_str("<table>\n");
+        html.push_str("<tr><th>Package</th><th>Change</th><th>Version</th></tr>\n");
+        for package in &report.package_changes {
+            html.push_str(&format!(
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                escape(&package.name),
+                package_change_label(package.change),
+                escape(&version_span(
+                    package.old_version.as_deref(),
+                    package.new_version.as_deref()
+                ))
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+
+    if !report.files.is_empty() {
+        html.push_str("<h2>Changed Files</h2>\n");
+        html.push_str("<table>\n");
+        html.push_str("<tr><th>Path</th><th>Change</th><th>Package</th><th>Action</th><th>Evidence</th><th>Modified</th></tr>\n");
+        for file in &report.files {
+            html.push_str(&format!(
+                "<tr><td><code>{}</code></td><td class=\"{}\">{}</td><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>\n",
+                escape(&file.path),
+                file.change.as_str(),
+                file.change.as_str(),
+                escape(file.package.as_deref().unwrap_or("")),
+                file.action.as_str(),
+                escape(&file.evidence),
+                file.modified_at.as_deref().unwrap_or("")
+            ));
+        }
+        html.push_str("</table>\n");
+    }
+
+    if !report.warnings.is_empty() {
+        html.push_str("<h2>Warnings</h2>\n<ul>\n");
+        for warning in &report.warnings {
+            html.push_str(&format!("<li>{}</li>\n", escape(warning)));
+        }
+        html.push_str("</ul>\n");
+    }
+
+    html.push_str("</body>\n</html>\n");
+
+    html
+}
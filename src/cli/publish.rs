@@ -0,0 +1,53 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! `convert --publish`: upload the converted image to Glance or Proxmox VE
+
+use crate::cli::plain;
+use anyhow::{Context, Result};
+use colored::Colorize;
+use guestkit::publish::{ImageProperties, PublishTarget};
+use std::path::Path;
+
+/// Parse a `--publish` value (clap value parser)
+pub fn parse_publish_target(value: &str) -> Result<PublishTarget, String> {
+    value.parse().map_err(|e: guestkit::Error| e.to_string())
+}
+
+/// Inspect `image` once and publish it to every target
+pub fn publish_image(image: &Path, format: &str, targets: &[PublishTarget]) -> Result<()> {
+    if targets.is_empty() {
+        return Ok(());
+    }
+
+    let properties = ImageProperties::detect(image)
+        .with_context(|| format!("Failed to inspect {}", image.display()))?;
+    if let Some(product) = properties
+        .product_name
+        .as_ref()
+        .or(properties.os_type.as_ref())
+    {
+        println!(
+            "  Guest:   {} ({})",
+            product,
+            properties
+                .disk_bus
+                .map(|bus| bus.to_string())
+                .unwrap_or_else(|| "default bus".to_string())
+        );
+    }
+
+    for target in targets {
+        println!("Publishing to {}...", target);
+        let published = target
+            .publish(image, format, &properties)
+            .with_context(|| format!("Failed to publish to {}", target))?;
+
+        println!("{} Published {}", plain::text("✓").green(), published.id.bold());
+        if let Some(vmid) = published.vmid {
+            println!("  VM:      {}", vmid);
+        }
+        for (key, value) in &published.properties {
+            println!("  {:<20} {}", format!("{}:", key), value);
+        }
+    }
+    Ok(())
+}
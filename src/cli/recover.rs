@@ -0,0 +1,243 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Deleted file recovery
+//!
+//! `recover` carves deleted files out of every ext2/3/4 and NTFS
+//! filesystem of an image into a directory, one subdirectory per
+//! filesystem, and lists what came from where in `recovered.json`. Nothing
+//! is mounted, so the filesystems are read as the guest left them.
+
+use crate::cli::macb::Filter;
+use crate::cli::target;
+use anyhow::Result;
+use guestkit::Guestfs;
+use serde::Serialize;
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+
+/// Name of the report written next to the recovered files
+pub const REPORT_NAME: &str = "recovered.json";
+
+/// A recovered file
+#[derive(Debug, Clone, Serialize)]
+pub struct Recovered {
+    pub device: String,
+    /// Inode, or MFT record number
+    pub inode: u64,
+    /// Where it was found: `inode`, `journal`, `orphan` or `mft`
+    pub source: String,
+    /// Path in the guest, if a name survived
+    pub guest_path: Option<String>,
+    /// Where it was written, relative to the output directory
+    pub output_path: String,
+    pub size: u64,
+    pub mtime: Option<String>,
+    pub deleted_at: Option<String>,
+    /// Some blocks were reused since, so parts hold other files' data
+    pub overwritten: bool,
+    pub sha256: String,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Report {
+    pub image_path: String,
+    pub output_dir: String,
+    pub started_at: String,
+    pub completed_at: String,
+    /// Filesystems searched
+    pub filesystems: Vec<String>,
+    pub recovered: Vec<Recovered>,
+    /// What could not be searched or written, and why
+    pub errors: Vec<String>,
+}
+
+impl Report {
+    pub fn overwritten(&self) -> usize {
+        self.recovered.iter().filter(|r| r.overwritten).count()
+    }
+}
+
+fn rfc3339(time: i64) -> Option<String> {
+    (time > 0)
+        .then(|| chrono::DateTime::from_timestamp(time, 0))
+        .flatten()
+        .map(|time| time.to_rfc3339())
+}
+
+/// Where a deleted file goes, relative to the output directory: under the
+/// device's name, at its guest path if it has one
+pub fn relative_path(device: &str, guest_path: Option<&str>, inode: u64) -> PathBuf {
+    let mut path = PathBuf::from(device.trim_start_matches("/dev/").replace('/', "_"));
+    match guest_path {
+        Some(guest_path) => {
+            for part in guest_path.split('/') {
+                if !part.is_empty() && part != "." && part != ".." {
+                    path.push(part.replace('\0', "_"));
+                }
+            }
+        }
+        None => path.push(format!("unnamed/inode-{}", inode)),
+    }
+    path
+}
+
+/// Recover the deleted files under `paths` (all if empty) into `output`
+pub fn recover(image: &Path, output: &Path, paths: &[String], verbose: bool) -> Result<Report> {
+    let started_at = chrono::Utc::now().to_rfc3339();
+    std::fs::create_dir_all(output)?;
+
+    let mut g = Guestfs::new()?;
+    g.set_verbose(verbose);
+    g.add_drive_opts(image, true, None)?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
+    g.launch()?;
+
+    let mut filesystems: Vec<(String, String)> = g
+        .list_filesystems()?
+        .into_iter()
+        .filter(|(_, fs_type)| matches!(fs_type.as_str(), "ext2" | "ext3" | "ext4" | "ntfs"))
+        .collect();
+    filesystems.sort();
+    if filesystems.is_empty() {
+        anyhow::bail!("No ext2/3/4 or NTFS filesystems found in disk image");
+    }
+
+    let filter = Filter {
+        paths: paths.to_vec(),
+        ..Filter::default()
+    };
+    let mut recovered = Vec::new();
+    let mut errors = Vec::new();
+    for (device, fs_type) in &filesystems {
+        let deleted = match g.deleted_files(device) {
+            Ok(deleted) => deleted,
+            Err(e) => {
+                errors.push(format!("{} ({}): {}", device, fs_type, e));
+                continue;
+            }
+        };
+        if verbose {
+            eprintln!("{} ({}): {} deleted files", device, fs_type, deleted.len());
+        }
+
+        for file in deleted {
+            let keep = match &file.path {
+                Some(path) => filter.matches_path(path),
+                None => paths.is_empty(),
+            };
+            if !keep {
+                continue;
+            }
+
+            // Versions of a file deleted more than once share a path
+            let mut relative = relative_path(device, file.path.as_deref(), file.inode);
+            if output.join(&relative).exists() {
+                let name = relative.file_name().unwrap_or_default().to_string_lossy();
+                relative.set_file_name(format!("{}.inode-{}", name, file.inode));
+            }
+            let target = output.join(&relative);
+            if let Some(parent) = target.parent() {
+                if let Err(e) = std::fs::create_dir_all(parent) {
+                    errors.push(format!("{}: {}", parent.display(), e));
+                    continue;
+                }
+            }
+            if let Err(e) = g.recover_deleted(&file, &target.to_string_lossy()) {
+                errors.push(format!("{} inode {}: {}", device, file.inode, e));
+                continue;
+            }
+
+            let sha256 = Sha256::digest(std::fs::read(&target)?)
+                .iter()
+                .map(|b| format!("{:02x}", b))
+                .collect();
+            recovered.push(Recovered {
+                device: device.clone(),
+                inode: file.inode,
+                source: file.source.to_string(),
+                guest_path: file.path.clone(),
+                output_path: relative.to_string_lossy().into_owned(),
+                size: file.size,
+                mtime: rfc3339(file.mtime),
+                deleted_at: rfc3339(file.deleted),
+                overwritten: file.overwritten,
+                sha256,
+            });
+        }
+    }
+    g.shutdown()?;
+
+    Ok(Report {
+        image_path: image.display().to_string(),
+        output_dir: output.display().to_string(),
+        started_at,
+        completed_at: chrono::Utc::now().to_rfc3339(),
+        filesystems: filesystems
+            .into_iter()
+            .map(|(device, fs_type)| format!("{} ({})", device, fs_type))
+            .collect(),
+        recovered,
+        errors,
+    })
+}
+
+/// Format the report as text
+pub fn format_report(report: &Report) -> String {
+    let mut output = String::new();
+
+    output.push_str("🗑️  Deleted File Recovery\n");
+    output.push_str("=========================\n\n");
+    output.push_str(&format!("Image: {}\n", report.image_path));
+    output.push_str(&format!("Filesystems: {}\n", report.filesystems.join(", ")));
+    output.push_str(&format!(
+        "Recovered: {} files, {} partly overwritten\n\n",
+        report.recovered.len(),
+        report.overwritten()
+    ));
+
+    for file in &report.recovered {
+        output.push_str(&format!(
+            "{:<8} {:>10}  {}{}\n",
+            file.source,
+            file.size,
+            file.guest_path.as_deref().unwrap_or("(no name)"),
+            if file.overwritten {
+                "  ⚠️  overwritten"
+            } else {
+                ""
+            }
+        ));
+        output.push_str(&format!("{:<8} {:>10}  → {}\n", "", "", file.output_path));
+    }
+
+    if !report.errors.is_empty() {
+        output.push_str("\n⚠️  Errors\n");
+        output.push_str("----------\n");
+        for error in &report.errors {
+            output.push_str(&format!("{}\n", error));
+        }
+    }
+
+    output
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_relative_path() {
+        assert_eq!(
+            relative_path("/dev/sda2", Some("/home/alice/notes.txt"), 12),
+            PathBuf::from("sda2/home/alice/notes.txt")
+        );
+        assert_eq!(
+            relative_path("/dev/vg0/root", Some("/../../etc/./shadow"), 12),
+            PathBuf::from("vg0_root/etc/shadow")
+        );
+        assert_eq!(
+            relative_path("/dev/sda1", None, 4711),
+            PathBuf::from("sda1/unnamed/inode-4711")
+        );
+    }
+}
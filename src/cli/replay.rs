@@ -0,0 +1,86 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Replay command - rerun an inspection from a recorded session trace
+//!
+//! `guestctl --record-trace FILE inspect IMAGE` saves what the inspection
+//! calls returned; `guestctl replay FILE` produces the same report from
+//! the trace alone, without the image.
+
+use crate::cli::commands::inspect_image;
+use crate::cli::formatters::OutputFormat;
+use crate::cli::plain;
+use anyhow::{Context, Result};
+use clap::Args;
+use colored::*;
+use guestkit::guestfs::session_trace::{self, SessionTrace};
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Args)]
+pub struct ReplayCommand {
+    /// Session trace written by --record-trace
+    pub trace: PathBuf,
+
+    /// Output format (text, json, yaml, csv)
+    #[arg(short, long)]
+    pub output: Option<String>,
+
+    /// Enable verbose output
+    #[arg(short, long)]
+    pub verbose: bool,
+}
+
+impl ReplayCommand {
+    pub fn execute(&self) -> Result<()> {
+        let trace = read_trace(&self.trace)?;
+        let output_format = self
+            .output
+            .as_ref()
+            .map(|s| s.parse::<OutputFormat>())
+            .transpose()
+            .map_err(|e| anyhow::anyhow!("{}", e))?;
+
+        eprintln!(
+            "{} Replaying {} calls recorded {} by guestkit {}",
+            plain::text("→").cyan(),
+            trace.calls.len(),
+            trace.recorded_at,
+            trace.guestkit_version
+        );
+        session_trace::set_replay(Some(trace));
+        let result = inspect_image(
+            &self.trace,
+            &[],
+            self.verbose,
+            false,
+            output_format,
+            None,
+            None,
+            None,
+            false,
+            false,
+        );
+        session_trace::set_replay(None);
+        result
+    }
+}
+
+fn read_trace(path: &Path) -> Result<SessionTrace> {
+    let json = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read {}", path.display()))?;
+    SessionTrace::from_json(&json).with_context(|| format!("Failed to load {}", path.display()))
+}
+
+/// Stop recording and write the trace to `path`
+pub fn save_recording(path: &Path) -> Result<()> {
+    let Some(trace) = session_trace::finish_recording() else {
+        return Ok(());
+    };
+    std::fs::write(path, trace.to_json()?)
+        .with_context(|| format!("Failed to write {}", path.display()))?;
+    eprintln!(
+        "{} Recorded {} calls to {} (review it before sharing)",
+        plain::text("✓").green(),
+        trace.calls.len(),
+        path.display()
+    );
+    Ok(())
+}
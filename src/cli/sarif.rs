@@ -0,0 +1,191 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! SARIF 2.1.0 logs, the format code scanning dashboards import
+//!
+//! Results point at guest paths, which are written as absolute-path URI
+//! references.
+
+use serde::Serialize;
+
+pub const VERSION: &str = "2.1.0";
+pub const SCHEMA: &str = "https://json.schemastore.org/sarif-2.1.0.json";
+
+#[derive(Debug, Serialize)]
+pub struct Log {
+    #[serde(rename = "$schema")]
+    pub schema: String,
+    pub version: String,
+    pub runs: Vec<Run>,
+}
+
+impl Log {
+    /// A log of one run
+    pub fn new(driver: Driver, results: Vec<SarifResult>) -> Self {
+        Self {
+            schema: SCHEMA.to_string(),
+            version: VERSION.to_string(),
+            runs: vec![Run {
+                tool: Tool { driver },
+                results,
+            }],
+        }
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Run {
+    pub tool: Tool,
+    pub results: Vec<SarifResult>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct Tool {
+    pub driver: Driver,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Driver {
+    pub name: String,
+    pub version: String,
+    pub information_uri: String,
+    pub rules: Vec<ReportingDescriptor>,
+}
+
+impl Driver {
+    /// guestctl, with the given rules
+    pub fn guestctl(rules: Vec<ReportingDescriptor>) -> Self {
+        Self {
+            name: "guestctl".to_string(),
+            version: env!("CARGO_PKG_VERSION").to_string(),
+            information_uri: "https://github.com/ssahani/guestkit".to_string(),
+            rules,
+        }
+    }
+}
+
+/// A rule results refer to by ID
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ReportingDescriptor {
+    pub id: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub short_description: Option<Message>,
+    /// Anything else known about the rule, e.g. YARA metadata
+    #[serde(skip_serializing_if = "serde_json::Map::is_empty")]
+    pub properties: serde_json::Map<String, serde_json::Value>,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct SarifResult {
+    pub rule_id: String,
+    pub level: String,
+    pub message: Message,
+    pub locations: Vec<Location>,
+}
+
+impl SarifResult {
+    /// A result about a guest file, with guestctl's severity
+    pub fn new(rule_id: &str, severity: &str, message: String, path: &str) -> Self {
+        Self {
+            rule_id: rule_id.to_string(),
+            level: level(severity).to_string(),
+            message: Message { text: message },
+            locations: vec![Location {
+                physical_location: PhysicalLocation {
+                    artifact_location: ArtifactLocation {
+                        uri: path.to_string(),
+                    },
+                    region: None,
+                },
+            }],
+        }
+    }
+
+    /// Point the result at a byte range of its file
+    pub fn with_bytes(mut self, offset: usize, length: usize) -> Self {
+        for location in &mut self.locations {
+            location.physical_location.region = Some(Region {
+                byte_offset: offset,
+                byte_length: length,
+            });
+        }
+        self
+    }
+}
+
+#[derive(Debug, Serialize)]
+pub struct Message {
+    pub text: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Location {
+    pub physical_location: PhysicalLocation,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct PhysicalLocation {
+    pub artifact_location: ArtifactLocation,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub region: Option<Region>,
+}
+
+#[derive(Debug, Serialize)]
+pub struct ArtifactLocation {
+    pub uri: String,
+}
+
+#[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Region {
+    pub byte_offset: usize,
+    pub byte_length: usize,
+}
+
+/// SARIF level of a CRITICAL/HIGH/MEDIUM/LOW severity
+pub fn level(severity: &str) -> &'static str {
+    match severity.to_ascii_uppercase().as_str() {
+        "CRITICAL" | "HIGH" => "error",
+        "MEDIUM" => "warning",
+        _ => "note",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_log() {
+        let rule = ReportingDescriptor {
+            id: "yara/default.EICAR".to_string(),
+            short_description: Some(Message {
+                text: "EICAR test file".to_string(),
+            }),
+            properties: serde_json::Map::new(),
+        };
+        let result = SarifResult::new(
+            "yara/default.EICAR",
+            "HIGH",
+            "EICAR matched".to_string(),
+            "/tmp/eicar.com",
+        )
+        .with_bytes(0, 68);
+        let log = Log::new(Driver::guestctl(vec![rule]), vec![result]);
+
+        let json = serde_json::to_value(&log).unwrap();
+        assert_eq!(json["version"], "2.1.0");
+        let run = &json["runs"][0];
+        assert_eq!(run["tool"]["driver"]["name"], "guestctl");
+        assert!(run["tool"]["driver"]["rules"][0]
+            .get("properties")
+            .is_none());
+        assert_eq!(run["results"][0]["level"], "error");
+        let location = &run["results"][0]["locations"][0]["physicalLocation"];
+        assert_eq!(location["artifactLocation"]["uri"], "/tmp/eicar.com");
+        assert_eq!(location["region"]["byteLength"], 68);
+    }
+}
@@ -0,0 +1,60 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Shannon entropy of candidate secrets
+//!
+//! Random tokens score high, words and repeated characters low. Thresholds
+//! are given in bits per character for the base64 alphabet; hex strings
+//! can reach only 4 bits, so their threshold is scaled down by the ratio.
+
+/// Shannon entropy in bits per character
+pub fn shannon(s: &str) -> f64 {
+    let mut counts = [0usize; 256];
+    let mut len = 0usize;
+    for b in s.bytes() {
+        counts[b as usize] += 1;
+        len += 1;
+    }
+    if len == 0 {
+        return 0.0;
+    }
+
+    counts
+        .iter()
+        .filter(|&&n| n > 0)
+        .map(|&n| {
+            let p = n as f64 / len as f64;
+            -p * p.log2()
+        })
+        .sum()
+}
+
+/// Whether `s` is random enough for a threshold given for base64 tokens
+pub fn exceeds(s: &str, threshold: f64) -> bool {
+    let is_hex = !s.is_empty() && s.bytes().all(|b| b.is_ascii_hexdigit());
+    let threshold = if is_hex {
+        threshold * 4.0 / 6.0
+    } else {
+        threshold
+    };
+    shannon(s) >= threshold
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_shannon() {
+        assert_eq!(shannon(""), 0.0);
+        assert_eq!(shannon("aaaaaaaa"), 0.0);
+        assert!((shannon("abab") - 1.0).abs() < 1e-9);
+        assert!((shannon("0123456789abcdef") - 4.0).abs() < 1e-9);
+    }
+
+    #[test]
+    fn test_exceeds() {
+        assert!(exceeds("wJalrXUtnFEMI/K7MDENG/bPxRfiCYEXAMPLEKEY", 4.0));
+        assert!(!exceeds("passwordpasswordpassword", 4.0));
+        // A SHA-1 in hex is held to the scaled threshold
+        assert!(exceeds("3f786850e387550fdab836ed7e6dc881de23001b", 4.0));
+    }
+}
@@ -0,0 +1,291 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Secret detection rules and allowlists
+//!
+//! Rule packs are TOML files with `[[rules]]` and an `[allowlist]`, the
+//! shape of the built-in pack below:
+//!
+//! ```toml
+//! [[rules]]
+//! id = "internal-token"
+//! description = "Internal API token"
+//! regex = '''\b(itk_[0-9a-f]{32})\b'''
+//! keywords = ["itk_"]
+//!
+//! [allowlist]
+//! paths = ["/opt/app/tests/"]
+//! regexes = ['''^itk_0{32}$''']
+//! rules = ["password-assignment"]
+//! ```
+//!
+//! The secret is the first capture group, or the whole match without one.
+//! Keywords are matched case-insensitively before the regex runs, and
+//! `entropy` is the minimum Shannon entropy of the secret. Allowlist paths
+//! are gitignore-style, regexes are tested against the secret, and `rules`
+//! turns off rules by ID.
+
+use crate::cli::matcher::MatchSet;
+use anyhow::{Context, Result};
+use regex::Regex;
+use serde::Deserialize;
+use std::path::Path;
+
+/// The rules `secrets` runs unless told otherwise
+pub const BUILTIN: &str = r#####"
+[[rules]]
+id = "aws-access-key-id"
+description = "AWS Access Key ID"
+regex = '''\b((?:AKIA|ASIA|ABIA|ACCA)[A-Z0-9]{16})\b'''
+keywords = ["akia", "asia", "abia", "acca"]
+
+[[rules]]
+id = "aws-secret-access-key"
+description = "AWS Secret Access Key"
+regex = '''(?i)aws_?secret_?(?:access_?)?key["']?\s*[:=]\s*["']?([A-Za-z0-9/+=]{40})(?:[^A-Za-z0-9/+=]|$)'''
+keywords = ["aws"]
+entropy = 3.5
+
+[[rules]]
+id = "gcp-api-key"
+description = "Google Cloud API Key"
+regex = '''\b(AIza[0-9A-Za-z_\-]{35})\b'''
+keywords = ["aiza"]
+
+[[rules]]
+id = "gcp-service-account-key"
+description = "Google Cloud Service Account Key"
+regex = '''"private_key_id"\s*:\s*"([0-9a-f]{40})"'''
+keywords = ["private_key_id"]
+
+[[rules]]
+id = "gcp-oauth-client-secret"
+description = "Google OAuth Client Secret"
+regex = '''\b(GOCSPX-[0-9A-Za-z_\-]{28})\b'''
+keywords = ["gocspx-"]
+
+[[rules]]
+id = "azure-storage-account-key"
+description = "Azure Storage Account Key"
+regex = '''(?i)AccountKey\s*=\s*([A-Za-z0-9+/]{86}==)'''
+keywords = ["accountkey"]
+
+[[rules]]
+id = "azure-sas-token"
+description = "Azure Shared Access Signature"
+regex = '''[?&]sig=([A-Za-z0-9%+/]{43,}(?:%3D|=){0,2})'''
+keywords = ["sig="]
+
+[[rules]]
+id = "azure-client-secret"
+description = "Azure AD Client Secret"
+regex = '''\b([A-Za-z0-9_~.]{3}\dQ~[A-Za-z0-9_~.\-]{31,34})'''
+keywords = ["q~"]
+
+[[rules]]
+id = "jwt"
+description = "JSON Web Token"
+regex = '''\b(eyJ[A-Za-z0-9_\-]{10,}\.eyJ[A-Za-z0-9_\-]{10,}\.[A-Za-z0-9_\-]{10,})'''
+keywords = ["eyj"]
+
+[[rules]]
+id = "private-key"
+description = "Private Key"
+regex = '''-----BEGIN (?:RSA |DSA |EC |OPENSSH |ENCRYPTED |PGP )?PRIVATE KEY(?: BLOCK)?-----'''
+keywords = ["private key"]
+
+[[rules]]
+id = "github-token"
+description = "GitHub Token"
+regex = '''\b((?:ghp|gho|ghu|ghs|ghr)_[A-Za-z0-9]{36}|github_pat_[A-Za-z0-9_]{82})\b'''
+keywords = ["ghp_", "gho_", "ghu_", "ghs_", "ghr_", "github_pat_"]
+
+[[rules]]
+id = "gitlab-token"
+description = "GitLab Personal Access Token"
+regex = '''\b(glpat-[A-Za-z0-9_\-]{20})\b'''
+keywords = ["glpat-"]
+
+[[rules]]
+id = "slack-token"
+description = "Slack Token"
+regex = '''\b(xox[baprs]-[0-9A-Za-z\-]{10,})'''
+keywords = ["xox"]
+
+[[rules]]
+id = "stripe-live-key"
+description = "Stripe Live Key"
+regex = '''\b((?:sk|rk)_live_[0-9A-Za-z]{24,})\b'''
+keywords = ["_live_"]
+
+[[rules]]
+id = "connection-string"
+description = "Database Connection String"
+regex = '''(?i)\b(?:mongodb(?:\+srv)?|mysql|postgres(?:ql)?|redis|amqps?)://[^:/@\s]+:([^@\s]+)@'''
+keywords = ["://"]
+
+[[rules]]
+id = "password-assignment"
+description = "Password"
+regex = '''(?i)\b(?:password|passwd|pwd)["']?\s*[:=]\s*["']?([^\s"']{8,})'''
+keywords = ["password", "passwd", "pwd"]
+
+[[rules]]
+id = "generic-secret"
+description = "High-Entropy Secret"
+regex = '''(?i)\b[\w.\-]*(?:api_?key|secret|token|credential|auth_?key)["']?\s*[:=]\s*["']?([A-Za-z0-9+/=_\-.~]{20,})'''
+keywords = ["key", "secret", "token", "credential"]
+entropy = 4.0
+
+[allowlist]
+regexes = [
+    '''(?i)^(?:x{4,}|\*{4,}|changeme|change_me|example\w*|your[_\-]?\w*|placeholder|dummy|redacted)$''',
+    '''^\$\{[^}]*\}$''',
+    '''^\$\w+$''',
+    '''^<[^>]*>$''',
+    '''^\{\{.*\}\}$''',
+    '''^%\(\w+\)s$''',
+]
+"#####;
+
+/// A rule pack as written
+#[derive(Debug, Default, Deserialize)]
+pub struct RulePack {
+    #[serde(default)]
+    pub rules: Vec<RuleDef>,
+    #[serde(default)]
+    pub allowlist: AllowlistDef,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct RuleDef {
+    pub id: String,
+    pub description: String,
+    pub regex: String,
+    #[serde(default)]
+    pub keywords: Vec<String>,
+    pub entropy: Option<f64>,
+}
+
+#[derive(Debug, Default, Deserialize)]
+pub struct AllowlistDef {
+    #[serde(default)]
+    pub paths: Vec<String>,
+    #[serde(default)]
+    pub regexes: Vec<String>,
+    #[serde(default)]
+    pub rules: Vec<String>,
+}
+
+impl RulePack {
+    pub fn builtin() -> Self {
+        toml::from_str(BUILTIN).expect("built-in rule pack")
+    }
+
+    pub fn parse(text: &str) -> Result<Self> {
+        Ok(toml::from_str(text)?)
+    }
+
+    pub fn load(path: &Path) -> Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        Self::parse(&text).with_context(|| format!("Invalid rule pack {}", path.display()))
+    }
+}
+
+/// A compiled rule
+#[derive(Debug)]
+pub struct Rule {
+    pub id: String,
+    pub description: String,
+    pub regex: Regex,
+    /// Lowercase
+    pub keywords: Vec<String>,
+    pub entropy: Option<f64>,
+}
+
+impl Rule {
+    pub fn compile(def: &RuleDef) -> Result<Self> {
+        Ok(Self {
+            id: def.id.clone(),
+            description: def.description.clone(),
+            regex: Regex::new(&def.regex)
+                .with_context(|| format!("Invalid regex in rule '{}'", def.id))?,
+            keywords: def.keywords.iter().map(|k| k.to_lowercase()).collect(),
+            entropy: def.entropy,
+        })
+    }
+
+    /// Whether the rule can match text whose lowercase form is `lower`
+    pub fn may_match(&self, lower: &str) -> bool {
+        self.keywords.is_empty() || self.keywords.iter().any(|k| lower.contains(k.as_str()))
+    }
+}
+
+/// What is never reported
+#[derive(Debug, Default)]
+pub struct Allowlist {
+    paths: MatchSet,
+    regexes: Vec<Regex>,
+    pub rules: Vec<String>,
+}
+
+impl Allowlist {
+    pub fn add(&mut self, def: &AllowlistDef) -> Result<()> {
+        self.paths.add_all(&def.paths)?;
+        for re in &def.regexes {
+            self.regexes
+                .push(Regex::new(re).with_context(|| format!("Invalid allowlist regex '{}'", re))?);
+        }
+        self.rules.extend(def.rules.iter().cloned());
+        Ok(())
+    }
+
+    pub fn allows_path(&self, path: &str) -> bool {
+        self.paths.matches(path, false)
+    }
+
+    pub fn allows_secret(&self, secret: &str) -> bool {
+        self.regexes.iter().any(|re| re.is_match(secret))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_builtin_compiles() {
+        let pack = RulePack::builtin();
+        assert!(pack.rules.len() > 10);
+        for def in &pack.rules {
+            Rule::compile(def).unwrap();
+        }
+        Allowlist::default().add(&pack.allowlist).unwrap();
+    }
+
+    #[test]
+    fn test_pack() {
+        let pack = RulePack::parse(
+            r#"
+            [[rules]]
+            id = "internal-token"
+            description = "Internal API token"
+            regex = '\b(itk_[0-9a-f]{32})\b'
+            keywords = ["ITK_"]
+
+            [allowlist]
+            paths = ["tests/"]
+            rules = ["jwt"]
+            "#,
+        )
+        .unwrap();
+        let rule = Rule::compile(&pack.rules[0]).unwrap();
+        assert!(rule.may_match("token = itk_0123"));
+        assert!(!rule.may_match("nothing here"));
+
+        let mut allowlist = Allowlist::default();
+        allowlist.add(&pack.allowlist).unwrap();
+        assert!(allowlist.allows_path("/opt/app/tests/fixture.env"));
+        assert!(!allowlist.allows_path("/opt/app/.env"));
+        assert_eq!(allowlist.rules, ["jwt"]);
+    }
+}
@@ -0,0 +1,360 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Share command - serve a host directory or files pulled from an image to
+//! a running VM over virtiofs or 9p
+//!
+//! virtiofs is served by virtiofsd on a vhost-user socket, which QEMU can
+//! hot-plug and libvirt can attach to a running domain. QEMU serves 9p
+//! itself, so a 9p share only takes effect when the VM is started with it.
+
+use crate::cli::catalog::parse_image_ref;
+use crate::cli::plain;
+use crate::cli::target;
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use colored::*;
+use guestkit::core::escape::xml_escape;
+use guestkit::Guestfs;
+use std::os::unix::process::CommandExt;
+use std::path::{Path, PathBuf};
+use std::process::{Child, Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
+
+/// Where distributions install virtiofsd when it is not on PATH
+const VIRTIOFSD_PATHS: &[&str] = &[
+    "/usr/libexec/virtiofsd",
+    "/usr/lib/qemu/virtiofsd",
+    "/usr/lib/virtiofsd",
+];
+
+static INTERRUPTED: AtomicBool = AtomicBool::new(false);
+
+#[derive(Debug, Args)]
+pub struct ShareCommand {
+    /// Host directory, or a disk image to share a directory of (see --path)
+    pub source: PathBuf,
+
+    /// Directory inside the image to copy out and share
+    #[arg(long, value_name = "GUEST_PATH")]
+    pub path: Option<String>,
+
+    /// Sharing protocol
+    #[arg(long, default_value = "virtiofs", value_parser = ["virtiofs", "9p"])]
+    pub protocol: String,
+
+    /// Mount tag the guest mounts the share by
+    #[arg(long, default_value = "guestkit")]
+    pub tag: String,
+
+    /// vhost-user socket for virtiofsd (default: in the runtime directory)
+    #[arg(long, value_name = "PATH")]
+    pub socket: Option<PathBuf>,
+
+    /// Attach the share to this running libvirt domain (virtiofs only)
+    #[arg(long, value_name = "DOMAIN")]
+    pub domain: Option<String>,
+
+    /// Let the guest write to the share
+    #[arg(long)]
+    pub writable: bool,
+
+    /// Keep the files copied out of the image when the share stops
+    #[arg(long, requires = "path")]
+    pub keep: bool,
+
+    /// Enable verbose output
+    #[arg(short, long)]
+    pub verbose: bool,
+}
+
+impl ShareCommand {
+    pub fn execute(&self) -> Result<()> {
+        if self.domain.is_some() && self.protocol == "9p" {
+            bail!("libvirt can't hot-plug 9p shares; use --protocol virtiofs or restart the domain with the share");
+        }
+
+        // Removed when the share stops, unless --keep
+        let extracted = match &self.path {
+            Some(guest_path) => {
+                let image =
+                    parse_image_ref(&self.source.to_string_lossy()).map_err(anyhow::Error::msg)?;
+                Some(self.copy_out(&image, guest_path)?)
+            }
+            None => None,
+        };
+        let dir = match &extracted {
+            Some(tree) => tree.path().to_path_buf(),
+            None if self.source.is_dir() => self.source.canonicalize()?,
+            None => bail!(
+                "{} is not a directory; use --path to share a directory of an image",
+                self.source.display()
+            ),
+        };
+
+        let result = match self.protocol.as_str() {
+            "9p" => self.serve_9p(&dir),
+            _ => self.serve_virtiofs(&dir),
+        };
+
+        if let Some(tree) = extracted {
+            if self.keep {
+                let kept = tree.keep();
+                eprintln!("Kept the copied files in {}", kept.display());
+            }
+        }
+        result
+    }
+
+    /// Copy `guest_path` out of `image` into a temporary directory
+    fn copy_out(&self, image: &Path, guest_path: &str) -> Result<tempfile::TempDir> {
+        let tree = tempfile::Builder::new()
+            .prefix("guestkit-share-")
+            .tempdir()?;
+
+        let mut g = Guestfs::new()?;
+        g.set_verbose(self.verbose);
+        g.add_drive_ro(image)
+            .with_context(|| format!("Failed to add disk: {}", image.display()))?;
+        target::add_disks(&mut g, true)?;
+        target::select(&mut g);
+        g.launch().context("Failed to launch appliance")?;
+        let root = g
+            .inspect_os()?
+            .into_iter()
+            .next()
+            .context("No operating system found")?;
+        g.mount_os_ro(&root)?;
+
+        eprintln!("Copying {} out of {}...", guest_path, image.display());
+        g.copy_out(guest_path, &tree.path().to_string_lossy())
+            .with_context(|| format!("Failed to copy {} out of the image", guest_path))?;
+        let _ = g.shutdown();
+        Ok(tree)
+    }
+
+    fn serve_virtiofs(&self, dir: &Path) -> Result<()> {
+        let socket = self.socket.clone().unwrap_or_else(|| {
+            let runtime = std::env::var_os("XDG_RUNTIME_DIR")
+                .map(PathBuf::from)
+                .unwrap_or_else(std::env::temp_dir);
+            runtime.join(format!("guestkit-{}.sock", self.tag))
+        });
+        let _ = std::fs::remove_file(&socket);
+
+        let mut cmd = Command::new(find_virtiofsd()?);
+        cmd.arg("--socket-path")
+            .arg(&socket)
+            .arg("--shared-dir")
+            .arg(dir)
+            .arg("--cache")
+            .arg("auto")
+            // Out of the terminal's process group: Ctrl-C detaches the share first
+            .process_group(0);
+        if !self.writable {
+            cmd.arg("--readonly");
+        }
+        // Namespace sandboxing needs root
+        if unsafe { libc::geteuid() } != 0 {
+            cmd.arg("--sandbox").arg("none");
+        }
+        let mut virtiofsd = cmd
+            .stdin(Stdio::null())
+            .spawn()
+            .context("Failed to start virtiofsd")?;
+
+        // virtiofsd listens once the socket exists
+        let mut waited = Duration::ZERO;
+        while !socket.exists() {
+            if let Some(status) = virtiofsd.try_wait()? {
+                bail!("virtiofsd exited: {}", status);
+            }
+            if waited > Duration::from_secs(10) {
+                let _ = virtiofsd.kill();
+                bail!("virtiofsd did not create {}", socket.display());
+            }
+            std::thread::sleep(Duration::from_millis(100));
+            waited += Duration::from_millis(100);
+        }
+
+        let xml = virtiofs_xml(&socket, &self.tag);
+        match &self.domain {
+            Some(domain) => {
+                virsh("attach-device", domain, &xml).inspect_err(|_| {
+                    let _ = virtiofsd.kill();
+                })?;
+                eprintln!("{} Attached to domain {}", plain::text("✓").green(), domain.bold());
+            }
+            None => print_virtiofs_usage(&socket, &self.tag, &xml),
+        }
+        self.print_share(dir, "virtiofs");
+        println!("  Guest:   mount -t virtiofs {} /mnt", self.tag);
+        println!("\nPress Ctrl-C to stop sharing.");
+
+        let result = wait_for_interrupt(Some(&mut virtiofsd));
+        if let Some(domain) = &self.domain {
+            if let Err(e) = virsh("detach-device", domain, &xml) {
+                eprintln!("{} Failed to detach the share: {:#}", plain::text("⚠").yellow(), e);
+            }
+        }
+        let _ = virtiofsd.kill();
+        let _ = virtiofsd.wait();
+        let _ = std::fs::remove_file(&socket);
+        result
+    }
+
+    fn serve_9p(&self, dir: &Path) -> Result<()> {
+        println!("QEMU serves 9p itself; start the VM with:");
+        println!("  {}", ninep_qemu_arg(dir, &self.tag, self.writable));
+        println!("\nor add to the libvirt domain (virsh edit):");
+        for line in ninep_xml(dir, &self.tag, self.writable).lines() {
+            println!("  {}", line);
+        }
+        self.print_share(dir, "9p");
+        println!(
+            "  Guest:   mount -t 9p -o trans=virtio,version=9p2000.L {} /mnt",
+            self.tag
+        );
+        println!("\nPress Ctrl-C when the VM no longer needs the share.");
+        wait_for_interrupt(None)
+    }
+
+    fn print_share(&self, dir: &Path, protocol: &str) {
+        println!(
+            "\n{} Sharing {} over {} as {}{}",
+            plain::text("✓").green(),
+            dir.display().to_string().bright_blue(),
+            protocol,
+            self.tag.bold(),
+            if self.writable { "" } else { " (read-only)" }
+        );
+    }
+}
+
+fn find_virtiofsd() -> Result<PathBuf> {
+    let on_path = std::env::var_os("PATH")
+        .map(|paths| {
+            std::env::split_paths(&paths)
+                .map(|dir| dir.join("virtiofsd"))
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    on_path
+        .into_iter()
+        .chain(VIRTIOFSD_PATHS.iter().map(PathBuf::from))
+        .find(|path| path.is_file())
+        .context("virtiofsd not found; install the virtiofsd package")
+}
+
+fn print_virtiofs_usage(socket: &Path, tag: &str, xml: &str) {
+    println!("Attach to a running QEMU whose memory is shared (monitor commands):");
+    println!("  chardev-add socket,id={},path={}", tag, socket.display());
+    println!("  device_add vhost-user-fs-pci,chardev={},tag={}", tag, tag);
+    println!(
+        "\nor to a libvirt domain with shared memory (virsh attach-device DOMAIN FILE --live):"
+    );
+    for line in xml.lines() {
+        println!("  {}", line);
+    }
+}
+
+extern "C" fn on_interrupt(_: libc::c_int) {
+    INTERRUPTED.store(true, Ordering::SeqCst);
+}
+
+/// Block until Ctrl-C or SIGTERM, failing if `child` exits first
+fn wait_for_interrupt(mut child: Option<&mut Child>) -> Result<()> {
+    unsafe {
+        libc::signal(
+            libc::SIGINT,
+            on_interrupt as *const () as libc::sighandler_t,
+        );
+        libc::signal(
+            libc::SIGTERM,
+            on_interrupt as *const () as libc::sighandler_t,
+        );
+    }
+
+    while !INTERRUPTED.load(Ordering::SeqCst) {
+        if let Some(child) = child.as_deref_mut() {
+            if let Some(status) = child.try_wait()? {
+                bail!("virtiofsd exited: {}", status);
+            }
+        }
+        std::thread::sleep(Duration::from_millis(200));
+    }
+
+    Ok(())
+}
+
+/// Run `virsh ACTION DOMAIN FILE --live` with `xml` as the device
+fn virsh(action: &str, domain: &str, xml: &str) -> Result<()> {
+    let file = tempfile::Builder::new().suffix(".xml").tempfile()?;
+    std::fs::write(file.path(), xml)?;
+    let output = Command::new("virsh")
+        .arg(action)
+        .arg(domain)
+        .arg(file.path())
+        .arg("--live")
+        .output()
+        .context("Failed to run virsh. Is libvirt installed?")?;
+    if !output.status.success() {
+        bail!(
+            "virsh {} failed: {}",
+            action,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(())
+}
+
+fn virtiofs_xml(socket: &Path, tag: &str) -> String {
+    format!(
+        "<filesystem type='mount'>\n  <driver type='virtiofs' queue='1024'/>\n  \
+         <source socket='{}'/>\n  <target dir='{}'/>\n</filesystem>\n",
+        xml_escape(&socket.to_string_lossy()),
+        xml_escape(tag)
+    )
+}
+
+fn ninep_xml(dir: &Path, tag: &str, writable: bool) -> String {
+    format!(
+        "<filesystem type='mount' accessmode='squash'>\n  <source dir='{}'/>\n  \
+         <target dir='{}'/>\n{}</filesystem>\n",
+        xml_escape(&dir.to_string_lossy()),
+        xml_escape(tag),
+        if writable { "" } else { "  <readonly/>\n" }
+    )
+}
+
+fn ninep_qemu_arg(dir: &Path, tag: &str, writable: bool) -> String {
+    format!(
+        "-virtfs local,path={},mount_tag={},security_model=none{}",
+        dir.to_string_lossy().replace(',', ",,"),
+        tag,
+        if writable { "" } else { ",readonly=on" }
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_virtiofs_xml() {
+        let xml = virtiofs_xml(Path::new("/run/user/1000/guestkit-rescue.sock"), "rescue");
+        assert!(xml.contains("<driver type='virtiofs' queue='1024'/>"));
+        assert!(xml.contains("<source socket='/run/user/1000/guestkit-rescue.sock'/>"));
+        assert!(xml.contains("<target dir='rescue'/>"));
+    }
+
+    #[test]
+    fn test_ninep_config() {
+        let dir = Path::new("/tmp/logs,old");
+        assert_eq!(
+            ninep_qemu_arg(dir, "logs", false),
+            "-virtfs local,path=/tmp/logs,,old,mount_tag=logs,security_model=none,readonly=on"
+        );
+        assert!(ninep_xml(dir, "logs", false).contains("<readonly/>"));
+        assert!(!ninep_xml(dir, "logs", true).contains("<readonly/>"));
+    }
+}
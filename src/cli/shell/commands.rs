@@ -3,7 +3,10 @@
 
 use anyhow::Result;
 use guestkit::Guestfs;
+use guestkit::guestfs::preview::DEFAULT_PREVIEW_BYTES;
 use colored::Colorize;
+use crate::cli::output::parse_size;
+use crate::cli::preview;
 use std::collections::HashMap;
 use std::time::Instant;
 
@@ -137,17 +140,35 @@ pub fn cmd_ls(ctx: &mut ShellContext, args: &[&str]) -> Result<()> {
 }
 
 /// Show file contents
+///
+/// Files larger than `--max-bytes` (1 MiB by default) are shown as their
+/// first and last lines; binary files as a hex dump.
 pub fn cmd_cat(ctx: &mut ShellContext, args: &[&str]) -> Result<()> {
     if args.is_empty() {
-        eprintln!("{} cat <file>", "Usage:".yellow());
+        eprintln!("{} cat <file> [--max-bytes SIZE]", "Usage:".yellow());
         return Ok(());
     }
 
+    let max_bytes = match args.iter().position(|arg| *arg == "--max-bytes") {
+        Some(i) => match args.get(i + 1).map(|value| parse_size(value)) {
+            Some(Ok(max_bytes)) => max_bytes,
+            Some(Err(e)) => {
+                eprintln!("{} {}", "Error:".red(), e);
+                return Ok(());
+            }
+            None => {
+                eprintln!("{} cat <file> [--max-bytes SIZE]", "Usage:".yellow());
+                return Ok(());
+            }
+        },
+        None => DEFAULT_PREVIEW_BYTES,
+    };
+
     let path = resolve_path(&ctx.current_path, args[0]);
 
-    match ctx.guestfs.read_file(&path) {
-        Ok(contents) => {
-            print!("{}", String::from_utf8_lossy(&contents));
+    match ctx.guestfs.preview_file(&path, max_bytes) {
+        Ok(window) => {
+            print!("{}", preview::preview_text(&window));
             Ok(())
         }
         Err(e) => {
@@ -236,23 +257,40 @@ pub fn cmd_grep(ctx: &mut ShellContext, args: &[&str]) -> Result<()> {
 
     let pattern = args[0];
     let path = resolve_path(&ctx.current_path, args[1]);
+    let matcher = regex::Regex::new(&regex::escape(pattern))?;
 
-    match ctx.guestfs.read_file(&path) {
-        Ok(contents) => {
-            let text = String::from_utf8_lossy(&contents);
-            for (i, line) in text.lines().enumerate() {
-                if line.contains(pattern) {
-                    println!("{}:{}", format!("{}", i + 1).cyan(),
-                            line.replace(pattern, &pattern.red().to_string()));
-                }
-            }
-            Ok(())
+    let binary = match ctx.guestfs.is_binary_file(&path) {
+        Ok(binary) => binary,
+        Err(e) => {
+            eprintln!("{} {}", "Error:".red(), e);
+            return Ok(());
         }
+    };
+    let reader = match ctx.guestfs.open_file(&path) {
+        Ok(reader) => reader,
         Err(e) => {
             eprintln!("{} {}", "Error:".red(), e);
-            Ok(())
+            return Ok(());
         }
+    };
+
+    if binary {
+        let options = preview::GrepOptions {
+            limit: Some(1),
+            ..Default::default()
+        };
+        if preview::grep_lines(reader, &matcher, &options, |_| Ok(()))? > 0 {
+            println!("Binary file {} matches", path);
+        }
+        return Ok(());
     }
+
+    preview::grep_lines(reader, &matcher, &preview::GrepOptions::default(), |line| {
+        println!("{}:{}", format!("{}", line.line_no + 1).cyan(),
+                line.text.replace(pattern, &pattern.red().to_string()));
+        Ok(())
+    })?;
+    Ok(())
 }
 
 /// Show system information
@@ -1238,12 +1276,12 @@ pub fn cmd_diff(ctx: &mut ShellContext, args: &[&str]) -> Result<()> {
                 println!("  Size: {} bytes", stat.size.to_string().green());
                 println!("  Mode: {:o}", stat.mode);
 
-                if let Ok(content) = ctx.guestfs.read_file(file_path) {
-                    let lines: Vec<&str> = std::str::from_utf8(&content)
-                        .unwrap_or("")
-                        .lines()
-                        .collect();
-                    println!("  Lines: {}", lines.len().to_string().green());
+                if !ctx.guestfs.is_binary_file(file_path).unwrap_or(true) {
+                    if let Ok(reader) = ctx.guestfs.open_file(file_path) {
+                        if let Ok(lines) = preview::count_lines(reader) {
+                            println!("  Lines: {}", lines.to_string().green());
+                        }
+                    }
                 }
             }
         }
@@ -1558,6 +1596,7 @@ pub fn cmd_search(ctx: &mut ShellContext, args: &[&str]) -> Result<()> {
     println!("{} Searching for: {} in {}", "→".cyan(), pattern.yellow(), search_path.cyan());
     println!();
 
+    let content_matcher = regex::Regex::new(&regex::escape(pattern))?;
     let mut results = Vec::new();
 
     // Simple recursive search (simplified version)
@@ -1578,17 +1617,16 @@ pub fn cmd_search(ctx: &mut ShellContext, args: &[&str]) -> Result<()> {
 
             // Content search for files
             if search_content && !ctx.guestfs.is_dir(&full_path).unwrap_or(true) {
-                if let Ok(content) = ctx.guestfs.read_file(&full_path) {
-                    if let Ok(text) = std::str::from_utf8(&content) {
-                        if text.contains(pattern) {
-                            let lines: Vec<&str> = text.lines()
-                                .filter(|l| l.contains(pattern))
-                                .take(3)
-                                .collect();
-                            for line in lines {
-                                results.push((full_path.clone(), line.to_string(), "content".to_string()));
-                            }
-                        }
+                if !ctx.guestfs.is_binary_file(&full_path).unwrap_or(true) {
+                    if let Ok(reader) = ctx.guestfs.open_file(&full_path) {
+                        let options = preview::GrepOptions {
+                            limit: Some(3),
+                            ..Default::default()
+                        };
+                        let _ = preview::grep_lines(reader, &content_matcher, &options, |line| {
+                            results.push((full_path.clone(), line.text, "content".to_string()));
+                            Ok(())
+                        });
                     }
                 }
             }
@@ -1699,16 +1737,20 @@ pub fn cmd_batch(ctx: &mut ShellContext, args: &[&str]) -> Result<()> {
                 let full_path = resolve_path(&ctx.current_path, file);
                 println!("{}", format!("=== {} ===", full_path).yellow().bold());
 
-                match ctx.guestfs.read_file(&full_path) {
-                    Ok(content) => {
-                        if let Ok(text) = std::str::from_utf8(&content) {
-                            let lines: Vec<&str> = text.lines().take(20).collect();
-                            for line in lines {
-                                println!("{}", line);
-                            }
-                            if text.lines().count() > 20 {
-                                println!("{}", "... (truncated)".bright_black());
-                            }
+                if ctx.guestfs.is_binary_file(&full_path).unwrap_or(false) {
+                    println!("{}", "(binary file)".bright_black());
+                    println!();
+                    continue;
+                }
+                match ctx.guestfs.open_file(&full_path) {
+                    Ok(reader) => {
+                        // Read one line past the limit to know whether to mark truncation
+                        let mut lines = std::io::BufRead::lines(reader);
+                        for line in lines.by_ref().take(20).map_while(|l| l.ok()) {
+                            println!("{}", line);
+                        }
+                        if lines.next().is_some() {
+                            println!("{}", "... (truncated)".bright_black());
                         }
                     }
                     Err(e) => {
@@ -12,6 +12,8 @@ use crossterm::{
 use std::io::{stdout, Write};
 
 use super::commands::ShellContext;
+use crate::cli::preview;
+use guestkit::guestfs::preview::DEFAULT_PREVIEW_BYTES;
 
 /// File entry in the explorer
 #[derive(Clone)]
@@ -292,11 +294,7 @@ fn draw_explorer(state: &ExplorerState, ctx: &ShellContext) -> Result<()> {
             format_size(entry.size)
         };
 
-        let name_display = if entry.name.len() > 50 {
-            format!("{}...", &entry.name[..47])
-        } else {
-            entry.name.clone()
-        };
+        let name_display = crate::cli::output::truncate_display(&entry.name, 50);
 
         if is_selected {
             println!("{} {} {:<50} {:>12}",
@@ -390,21 +388,27 @@ fn show_help() -> Result<()> {
 
 /// View file content
 fn view_file(ctx: &mut ShellContext, path: &str) -> Result<()> {
-    let content = ctx.guestfs.cat(path)
+    let window = ctx.guestfs.preview_file(path, DEFAULT_PREVIEW_BYTES)
         .context("Failed to read file")?;
 
     // Show in pager-like view
     println!("\n{}", format!("╔═ Viewing: {} ═╗", path).cyan().bold());
 
-    let lines: Vec<&str> = content.lines().collect();
-    let max_lines = 100; // Show first 100 lines
+    if window.binary {
+        println!("{}", preview::preview_text(&window));
+    } else {
+        let content = window.head_text();
+        let lines: Vec<&str> = content.lines().collect();
+        let max_lines = 100; // Show first 100 lines
 
-    for (i, line) in lines.iter().take(max_lines).enumerate() {
-        println!("{:4} │ {}", (i + 1).to_string().bright_black(), line);
-    }
+        for (i, line) in lines.iter().take(max_lines).enumerate() {
+            println!("{:4} │ {}", (i + 1).to_string().bright_black(), line);
+        }
 
-    if lines.len() > max_lines {
-        println!("\n{}", format!("... ({} more lines)", lines.len() - max_lines).yellow());
+        if lines.len() > max_lines || window.is_truncated() {
+            let shown = lines.len().min(max_lines);
+            println!("\n{}", format!("... (showing {} lines of {} bytes)", shown, window.size).yellow());
+        }
     }
 
     println!("\n{}", "Press any key to return...".yellow());
@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: LGPL-3.0-or-later
 //! REPL (Read-Eval-Print Loop) for interactive shell
 
+use crate::cli::target;
 use anyhow::{Context, Result};
 use colored::Colorize;
 use rustyline::error::ReadlineError;
@@ -22,6 +23,8 @@ pub fn run_interactive_shell<P: AsRef<Path>>(image_path: P) -> Result<()> {
     let mut guestfs = Guestfs::new().context("Failed to create Guestfs handle")?;
     guestfs.add_drive_opts(image_path.as_ref().to_str().unwrap(), false, None)
         .context("Failed to add drive")?;
+    target::add_disks(&mut guestfs, false)?;
+    target::select(&mut guestfs);
     guestfs.launch().context("Failed to launch guestfs")?;
 
     // Inspect and mount
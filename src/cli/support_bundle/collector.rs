@@ -0,0 +1,262 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Curated file collection for support bundles
+
+use super::{BundleCategory, BundleEntry, BundleOptions, SkippedEntry};
+use guestkit::Guestfs;
+use std::path::Path;
+
+/// Config files larger than this are skipped rather than copied
+const MAX_CONFIG_BYTES: i64 = 1024 * 1024;
+
+/// Journal directory read through journalctl
+const JOURNAL_DIR: &str = "/var/log/journal";
+
+/// How a source is copied into the bundle
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Mode {
+    /// Copy the whole file
+    Config,
+    /// Keep only the last `max_lines` lines
+    Log,
+}
+
+/// A curated guest path; directories contribute their regular files
+struct Source {
+    category: BundleCategory,
+    path: &'static str,
+    mode: Mode,
+}
+
+const fn source(category: BundleCategory, path: &'static str, mode: Mode) -> Source {
+    Source { category, path, mode }
+}
+
+/// Files support engineers ask for when a converted VM misbehaves
+const SOURCES: &[Source] = &[
+    // System
+    source(BundleCategory::System, "/etc/os-release", Mode::Config),
+    source(BundleCategory::System, "/etc/hostname", Mode::Config),
+    source(BundleCategory::System, "/etc/hosts", Mode::Config),
+    source(BundleCategory::System, "/etc/selinux/config", Mode::Config),
+    source(BundleCategory::System, "/etc/sysctl.conf", Mode::Config),
+    source(BundleCategory::System, "/etc/sysctl.d", Mode::Config),
+    source(BundleCategory::System, "/etc/modprobe.d", Mode::Config),
+    source(BundleCategory::System, "/etc/cloud/cloud.cfg", Mode::Config),
+    // Boot
+    source(BundleCategory::Boot, "/etc/default/grub", Mode::Config),
+    source(BundleCategory::Boot, "/etc/kernel/cmdline", Mode::Config),
+    source(BundleCategory::Boot, "/boot/grub2/grub.cfg", Mode::Config),
+    source(BundleCategory::Boot, "/boot/grub/grub.cfg", Mode::Config),
+    source(BundleCategory::Boot, "/etc/dracut.conf", Mode::Config),
+    source(BundleCategory::Boot, "/etc/dracut.conf.d", Mode::Config),
+    source(BundleCategory::Boot, "/var/log/boot.log", Mode::Log),
+    // Logs
+    source(BundleCategory::Logs, "/var/log/dmesg", Mode::Log),
+    source(BundleCategory::Logs, "/var/log/kern.log", Mode::Log),
+    source(BundleCategory::Logs, "/var/log/messages", Mode::Log),
+    source(BundleCategory::Logs, "/var/log/syslog", Mode::Log),
+    source(BundleCategory::Logs, "/var/log/cloud-init.log", Mode::Log),
+    source(BundleCategory::Logs, "/var/log/cloud-init-output.log", Mode::Log),
+    // Storage
+    source(BundleCategory::Storage, "/etc/fstab", Mode::Config),
+    source(BundleCategory::Storage, "/etc/crypttab", Mode::Config),
+    source(BundleCategory::Storage, "/etc/multipath.conf", Mode::Config),
+    source(BundleCategory::Storage, "/etc/mdadm.conf", Mode::Config),
+    source(BundleCategory::Storage, "/etc/mdadm/mdadm.conf", Mode::Config),
+    // Network
+    source(BundleCategory::Network, "/etc/resolv.conf", Mode::Config),
+    source(BundleCategory::Network, "/etc/nsswitch.conf", Mode::Config),
+    source(BundleCategory::Network, "/etc/network/interfaces", Mode::Config),
+    source(BundleCategory::Network, "/etc/netplan", Mode::Config),
+    source(BundleCategory::Network, "/etc/sysconfig/network", Mode::Config),
+    source(BundleCategory::Network, "/etc/sysconfig/network-scripts", Mode::Config),
+    source(BundleCategory::Network, "/etc/NetworkManager/system-connections", Mode::Config),
+    source(BundleCategory::Network, "/etc/systemd/network", Mode::Config),
+    // Package history
+    source(BundleCategory::Packages, "/var/log/dnf.log", Mode::Log),
+    source(BundleCategory::Packages, "/var/log/dnf.rpm.log", Mode::Log),
+    source(BundleCategory::Packages, "/var/log/yum.log", Mode::Log),
+    source(BundleCategory::Packages, "/var/log/apt/history.log", Mode::Log),
+    source(BundleCategory::Packages, "/var/log/apt/term.log", Mode::Log),
+    source(BundleCategory::Packages, "/var/log/dpkg.log", Mode::Log),
+    source(BundleCategory::Packages, "/var/log/zypp/history", Mode::Log),
+    source(BundleCategory::Packages, "/var/log/pacman.log", Mode::Log),
+];
+
+/// Copy curated sources into `bundle_root`
+///
+/// Missing sources are ignored; sources that exist but cannot be collected
+/// are reported as skipped.
+pub fn collect(
+    g: &mut Guestfs,
+    bundle_root: &Path,
+    options: &BundleOptions,
+    verbose: bool,
+) -> (Vec<BundleEntry>, Vec<SkippedEntry>) {
+    let mut files = Vec::new();
+    let mut skipped = Vec::new();
+
+    for src in SOURCES {
+        if !g.exists(src.path).unwrap_or(false) {
+            continue;
+        }
+
+        let paths: Vec<String> = if g.is_dir(src.path).unwrap_or(false) {
+            g.ls(src.path)
+                .unwrap_or_default()
+                .into_iter()
+                .map(|name| format!("{}/{}", src.path, name))
+                .filter(|path| g.is_file(path).unwrap_or(false))
+                .collect()
+        } else {
+            vec![src.path.to_string()]
+        };
+
+        for path in paths {
+            if verbose {
+                println!("  Collecting {}", path);
+            }
+
+            match read_source(g, &path, src.mode, options.max_lines) {
+                Ok((text, truncated)) => {
+                    match store(bundle_root, src.category, &path, &text, truncated, options) {
+                        Ok(entry) => files.push(entry),
+                        Err(reason) => skipped.push(SkippedEntry { guest_path: path, reason }),
+                    }
+                }
+                Err(reason) => skipped.push(SkippedEntry { guest_path: path, reason }),
+            }
+        }
+    }
+
+    // Journal excerpt (needs journalctl on the host)
+    if g.is_dir(JOURNAL_DIR).unwrap_or(false) {
+        if verbose {
+            println!("  Collecting journal excerpt");
+        }
+
+        match g.journal_get_json(JOURNAL_DIR, options.max_lines as i32) {
+            Ok(text) => {
+                match store(bundle_root, BundleCategory::Journal, "/journal.json", &text, true, options) {
+                    Ok(mut entry) => {
+                        entry.guest_path = JOURNAL_DIR.to_string();
+                        files.push(entry);
+                    }
+                    Err(reason) => skipped.push(SkippedEntry {
+                        guest_path: JOURNAL_DIR.to_string(),
+                        reason,
+                    }),
+                }
+            }
+            Err(e) => skipped.push(SkippedEntry {
+                guest_path: JOURNAL_DIR.to_string(),
+                reason: e.to_string(),
+            }),
+        }
+    }
+
+    (files, skipped)
+}
+
+/// Read a source as text, returning whether it was truncated
+fn read_source(
+    g: &mut Guestfs,
+    path: &str,
+    mode: Mode,
+    max_lines: usize,
+) -> Result<(String, bool), String> {
+    let size = g.filesize(path).map_err(|e| e.to_string())?;
+
+    let text = match mode {
+        Mode::Config => {
+            if size > MAX_CONFIG_BYTES {
+                return Err(format!("file too large ({} bytes)", size));
+            }
+            let bytes = g.read_file(path).map_err(|e| e.to_string())?;
+            if is_binary(&bytes) {
+                return Err("binary file".to_string());
+            }
+            String::from_utf8_lossy(&bytes).to_string()
+        }
+        Mode::Log => {
+            let lines = g
+                .tail_n(max_lines as i32, path)
+                .map_err(|e| e.to_string())?;
+            let mut text = lines.join("\n");
+            text.push('\n');
+            text
+        }
+    };
+
+    let truncated = (text.len() as i64) < size;
+    Ok((text, truncated))
+}
+
+/// Redact and write one file into the bundle
+fn store(
+    bundle_root: &Path,
+    category: BundleCategory,
+    guest_path: &str,
+    text: &str,
+    truncated: bool,
+    options: &BundleOptions,
+) -> Result<BundleEntry, String> {
+    let (content, redactions) = match options.redactor {
+        Some(ref redactor) => redactor.redact(text),
+        None => (text.to_string(), 0),
+    };
+
+    let bundle_path = bundle_path(category, guest_path);
+    let host_path = bundle_root.join(&bundle_path);
+
+    if let Some(parent) = host_path.parent() {
+        std::fs::create_dir_all(parent).map_err(|e| e.to_string())?;
+    }
+    std::fs::write(&host_path, &content).map_err(|e| e.to_string())?;
+
+    Ok(BundleEntry {
+        category,
+        guest_path: guest_path.to_string(),
+        bundle_path,
+        size_bytes: content.len() as u64,
+        truncated,
+        redactions,
+    })
+}
+
+/// Location of a guest file inside the bundle, e.g. `network/etc/resolv.conf`
+fn bundle_path(category: BundleCategory, guest_path: &str) -> String {
+    let relative: Vec<&str> = guest_path
+        .split('/')
+        .filter(|part| !part.is_empty() && *part != "." && *part != "..")
+        .collect();
+    format!("{}/{}", category.as_str(), relative.join("/"))
+}
+
+/// Heuristic: NUL bytes in the first 8 KiB mean the file is not text
+fn is_binary(bytes: &[u8]) -> bool {
+    bytes.iter().take(8192).any(|&b| b == 0)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_bundle_path() {
+        assert_eq!(
+            bundle_path(BundleCategory::Network, "/etc/sysconfig/network-scripts/ifcfg-eth0"),
+            "network/etc/sysconfig/network-scripts/ifcfg-eth0"
+        );
+        assert_eq!(
+            bundle_path(BundleCategory::Storage, "/etc/../../fstab"),
+            "storage/etc/fstab"
+        );
+    }
+
+    #[test]
+    fn test_is_binary() {
+        assert!(!is_binary(b"UUID=1234 / xfs defaults 0 0\n"));
+        assert!(is_binary(b"\x7fELF\x02\x01\x01\x00"));
+    }
+}
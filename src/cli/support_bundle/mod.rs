@@ -0,0 +1,213 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Support bundle (sosreport-lite) collection module
+
+pub mod collector;
+pub mod redact;
+pub mod reporter;
+
+use crate::cli::plain;
+use crate::cli::target;
+use anyhow::{Context, Result};
+use guestkit::Guestfs;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+use redact::Redactor;
+
+/// Category of a collected file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum BundleCategory {
+    System,
+    Boot,
+    Journal,
+    Logs,
+    Storage,
+    Network,
+    Packages,
+}
+
+impl BundleCategory {
+    pub fn emoji(&self) -> &str {
+        match self {
+            Self::System => "🖥️",
+            Self::Boot => "🥾",
+            Self::Journal => "📓",
+            Self::Logs => "📜",
+            Self::Storage => "💾",
+            Self::Network => "🌐",
+            Self::Packages => "📦",
+        }
+    }
+
+    /// Directory name inside the bundle
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::System => "system",
+            Self::Boot => "boot",
+            Self::Journal => "journal",
+            Self::Logs => "logs",
+            Self::Storage => "storage",
+            Self::Network => "network",
+            Self::Packages => "packages",
+        }
+    }
+}
+
+/// A file stored in the bundle
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleEntry {
+    pub category: BundleCategory,
+    /// Source path in the guest
+    pub guest_path: String,
+    /// Path relative to the bundle root
+    pub bundle_path: String,
+    pub size_bytes: u64,
+    /// Only the tail of the file was kept
+    pub truncated: bool,
+    /// Number of values replaced by the redactor
+    pub redactions: usize,
+}
+
+/// A curated source that could not be collected
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SkippedEntry {
+    pub guest_path: String,
+    pub reason: String,
+}
+
+/// Bundle index, stored as `index.json` at the bundle root
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BundleIndex {
+    pub image_path: String,
+    pub created_at: String,
+    pub os_name: Option<String>,
+    pub hostname: Option<String>,
+    pub redacted: bool,
+    pub files: Vec<BundleEntry>,
+    pub skipped: Vec<SkippedEntry>,
+    /// Host path of the compressed bundle
+    pub archive: String,
+}
+
+impl BundleIndex {
+    pub fn total_redactions(&self) -> usize {
+        self.files.iter().map(|f| f.redactions).sum()
+    }
+
+    pub fn total_bytes(&self) -> u64 {
+        self.files.iter().map(|f| f.size_bytes).sum()
+    }
+}
+
+/// Bundle collection options
+#[derive(Debug, Clone)]
+pub struct BundleOptions {
+    /// Lines kept from the end of each log file
+    pub max_lines: usize,
+    /// Redactor applied to all text; `None` disables redaction
+    pub redactor: Option<Redactor>,
+}
+
+/// Collect a support bundle from a disk image into `archive` (.tar.gz)
+pub fn create_bundle<P: AsRef<Path>>(
+    image_path: P,
+    archive: &Path,
+    options: &BundleOptions,
+    verbose: bool,
+) -> Result<BundleIndex> {
+    let image_path_str = image_path.as_ref().display().to_string();
+
+    if verbose {
+        println!("{}Collecting support bundle from: {}", plain::text("🧰 "), image_path_str);
+    }
+
+    // Initialize guestfs
+    let mut g = Guestfs::new()?;
+    g.add_drive_opts(&image_path, true, None)?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
+    g.launch()?;
+
+    // Inspect OS
+    let roots = g.inspect_os()?;
+    if roots.is_empty() {
+        anyhow::bail!("No operating systems found in disk image");
+    }
+
+    let root = &roots[0];
+    let os_name = g.inspect_get_product_name(root).ok();
+    let hostname = g.inspect_get_hostname(root).ok();
+
+    // Mount filesystems
+    g.mount_os_ro(root)?;
+
+    // Stage files in a temporary directory named after the archive
+    let staging = tempfile::tempdir()?;
+    let bundle_name = bundle_name(archive);
+    let bundle_root = staging.path().join(&bundle_name);
+    std::fs::create_dir_all(&bundle_root)?;
+
+    let (files, skipped) = collector::collect(&mut g, &bundle_root, options, verbose);
+
+    // Shutdown guestfs
+    g.shutdown()?;
+
+    let index = BundleIndex {
+        image_path: image_path_str,
+        created_at: chrono::Utc::now().to_rfc3339(),
+        os_name,
+        hostname,
+        redacted: options.redactor.is_some(),
+        files,
+        skipped,
+        archive: archive.display().to_string(),
+    };
+
+    std::fs::write(
+        bundle_root.join("index.json"),
+        serde_json::to_string_pretty(&index)?,
+    )?;
+    std::fs::write(bundle_root.join("index.txt"), reporter::format_report(&index))?;
+
+    // Compress the staged bundle
+    let output = Command::new("tar")
+        .arg("-czf")
+        .arg(archive)
+        .arg("-C")
+        .arg(staging.path())
+        .arg(&bundle_name)
+        .output()
+        .context("Failed to run tar")?;
+
+    if !output.status.success() {
+        anyhow::bail!(
+            "Failed to create bundle archive: {}",
+            String::from_utf8_lossy(&output.stderr)
+        );
+    }
+
+    Ok(index)
+}
+
+/// Default archive path for an image: `<image-stem>-support-bundle.tar.gz`
+pub fn default_archive_path(image: &Path) -> PathBuf {
+    let stem = image
+        .file_stem()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "guest".to_string());
+    PathBuf::from(format!("{}-support-bundle.tar.gz", stem))
+}
+
+/// Top-level directory name inside the archive
+fn bundle_name(archive: &Path) -> String {
+    let name = archive
+        .file_name()
+        .map(|s| s.to_string_lossy().to_string())
+        .unwrap_or_else(|| "support-bundle".to_string());
+
+    name.trim_end_matches(".tar.gz")
+        .trim_end_matches(".tgz")
+        .to_string()
+}
+
@@ -0,0 +1,72 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Support bundle index formatting
+
+use super::BundleIndex;
+
+/// Format bundle index as text
+pub fn format_report(index: &BundleIndex) -> String {
+    let mut output = String::new();
+
+    output.push_str("🧰 Support Bundle\n");
+    output.push_str("=================\n\n");
+    output.push_str(&format!("Image: {}\n", index.image_path));
+    if let Some(ref os) = index.os_name {
+        output.push_str(&format!("OS: {}\n", os));
+    }
+    if let Some(ref hostname) = index.hostname {
+        output.push_str(&format!("Hostname: {}\n", hostname));
+    }
+    output.push_str(&format!("Created: {}\n", index.created_at));
+    output.push_str(&format!("Archive: {}\n\n", index.archive));
+
+    // Summary
+    output.push_str("📊 Summary\n");
+    output.push_str("----------\n");
+    output.push_str(&format!("Files collected: {}\n", index.files.len()));
+    output.push_str(&format!(
+        "Total size: {:.2} MB\n",
+        index.total_bytes() as f64 / 1_048_576.0
+    ));
+    if index.redacted {
+        output.push_str(&format!("Redactions: {}\n", index.total_redactions()));
+    } else {
+        output.push_str("Redactions: disabled\n");
+    }
+    output.push_str(&format!("Skipped: {}\n\n", index.skipped.len()));
+
+    // Contents
+    output.push_str("📁 Contents\n");
+    output.push_str("-----------\n");
+    for entry in &index.files {
+        let mut notes = Vec::new();
+        if entry.truncated {
+            notes.push("tail".to_string());
+        }
+        if entry.redactions > 0 {
+            notes.push(format!("{} redacted", entry.redactions));
+        }
+
+        output.push_str(&format!(
+            "{} {} ← {}",
+            entry.category.emoji(),
+            entry.bundle_path,
+            entry.guest_path
+        ));
+        if !notes.is_empty() {
+            output.push_str(&format!(" ({})", notes.join(", ")));
+        }
+        output.push('\n');
+    }
+    output.push('\n');
+
+    if !index.skipped.is_empty() {
+        output.push_str("⚠️  Skipped\n");
+        output.push_str("----------\n");
+        for skipped in &index.skipped {
+            output.push_str(&format!("{}: {}\n", skipped.guest_path, skipped.reason));
+        }
+        output.push('\n');
+    }
+
+    output
+}
@@ -0,0 +1,188 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Sync command - copy a directory tree from one image into another,
+//! writing only what changed
+
+use crate::cli::audit_log::audited;
+use crate::cli::catalog::parse_image_ref;
+use crate::cli::output::format_size;
+use crate::cli::plain;
+use crate::cli::target;
+use anyhow::{bail, Context, Result};
+use clap::Args;
+use colored::*;
+use guestkit::guestfs::{SyncOptions, SyncStats};
+use guestkit::Guestfs;
+use serde_json::json;
+use std::path::{Path, PathBuf};
+
+#[derive(Debug, Args)]
+pub struct SyncCommand {
+    /// Source as IMAGE:/PATH
+    #[arg(value_parser = parse_location, value_name = "SRC_IMAGE:PATH")]
+    pub source: Location,
+
+    /// Destination as IMAGE:/PATH
+    #[arg(value_parser = parse_location, value_name = "DST_IMAGE:PATH")]
+    pub destination: Location,
+
+    /// Delete destination files the source does not have
+    #[arg(long)]
+    pub delete: bool,
+
+    /// Compare contents of files whose size and mtime match
+    #[arg(short, long)]
+    pub checksum: bool,
+
+    /// Print the summary as JSON
+    #[arg(long)]
+    pub json: bool,
+
+    /// Enable verbose output
+    #[arg(short, long)]
+    pub verbose: bool,
+}
+
+/// A path inside an image
+#[derive(Debug, Clone)]
+pub struct Location {
+    pub image: PathBuf,
+    pub path: String,
+}
+
+/// Split IMAGE:/PATH at the last `:/`, so image paths may hold colons
+fn parse_location(value: &str) -> std::result::Result<Location, String> {
+    let (image, path) = value
+        .rfind(":/")
+        .map(|at| (&value[..at], &value[at + 1..]))
+        .ok_or_else(|| format!("'{}' is not IMAGE:/PATH", value))?;
+    if image.is_empty() {
+        return Err(format!("'{}' names no image", value));
+    }
+    Ok(Location {
+        image: parse_image_ref(image)?,
+        path: path.to_string(),
+    })
+}
+
+impl SyncCommand {
+    pub fn execute(&self) -> Result<()> {
+        let (src, dst) = (&self.source, &self.destination);
+        if same_file(&src.image, &dst.image) {
+            bail!(
+                "{} is both source and destination; it cannot be opened twice",
+                dst.image.display()
+            );
+        }
+
+        let stats = audited(
+            "sync",
+            &dst.image,
+            json!({
+                "source": src.image.display().to_string(),
+                "source_path": src.path,
+                "path": dst.path,
+                "delete": self.delete,
+                "checksum": self.checksum,
+            }),
+            || self.sync(),
+        )?;
+
+        if self.json {
+            println!("{}", serde_json::to_string_pretty(&stats)?);
+            return Ok(());
+        }
+
+        println!(
+            "{} Synced {}:{} to {}:{}",
+            plain::text("✓").green(),
+            src.image.display(),
+            src.path,
+            dst.image.display(),
+            dst.path
+        );
+        println!("  Created:   {}", stats.created);
+        println!("  Updated:   {}", stats.updated);
+        println!("  Unchanged: {}", stats.unchanged);
+        if self.delete {
+            println!("  Deleted:   {}", stats.deleted);
+        }
+        println!(
+            "  Data:      {} written, {} already there",
+            format_size(stats.bytes_written),
+            format_size(stats.bytes_matched)
+        );
+        Ok(())
+    }
+
+    fn sync(&self) -> Result<SyncStats> {
+        let mut source = Guestfs::new()?;
+        source.set_verbose(self.verbose);
+        source.add_drive_ro(&self.source.image)?;
+        target::add_disks(&mut source, true)?;
+        target::select(&mut source);
+        source.launch()?;
+        let roots = source.inspect_os()?;
+        let root = roots
+            .first()
+            .context("No operating system found in source")?;
+        source.mount_os_ro(root)?;
+
+        let result = self.sync_into(&mut source);
+        source.umount_all().ok();
+        source.shutdown().ok();
+        result
+    }
+
+    fn sync_into(&self, source: &mut Guestfs) -> Result<SyncStats> {
+        let mut dest = Guestfs::new()?;
+        dest.set_verbose(self.verbose);
+        dest.add_drive(&self.destination.image)?;
+        target::select(&mut dest);
+        dest.launch()?;
+        let roots = dest.inspect_os()?;
+        let root = roots
+            .first()
+            .context("No operating system found in destination")?;
+        dest.mount_os_rw(root)?;
+
+        let options = SyncOptions {
+            delete: self.delete,
+            checksum: self.checksum,
+        };
+        let stats = source.sync_to(
+            &self.source.path,
+            &mut dest,
+            &self.destination.path,
+            &options,
+        );
+        dest.umount_all().ok();
+        dest.shutdown().ok();
+        Ok(stats?)
+    }
+}
+
+fn same_file(a: &Path, b: &Path) -> bool {
+    match (a.canonicalize(), b.canonicalize()) {
+        (Ok(a), Ok(b)) => a == b,
+        _ => a == b,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_location() {
+        let location = parse_location("golden.qcow2:/etc").unwrap();
+        assert_eq!(location.image, PathBuf::from("golden.qcow2"));
+        assert_eq!(location.path, "/etc");
+
+        let location = parse_location("/srv/images/a:b.qcow2:/").unwrap();
+        assert_eq!(location.image, PathBuf::from("/srv/images/a:b.qcow2"));
+        assert_eq!(location.path, "/");
+
+        assert!(parse_location("golden.qcow2").is_err());
+        assert!(parse_location(":/etc").is_err());
+    }
+}
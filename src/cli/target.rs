@@ -0,0 +1,58 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! The OS that `--os-root` points commands at, and the disks `--disk` adds
+//!
+//! The options are global, while commands open their handles in many places.
+//! Each handle that opens the command's image is configured from here with
+//! [`add_disks`] and [`select`]; handles of other images are left alone.
+
+use anyhow::Result;
+use guestkit::guestfs::inspect::select_os_root;
+use guestkit::Guestfs;
+use std::path::PathBuf;
+use std::sync::OnceLock;
+
+static OS_ROOT: OnceLock<String> = OnceLock::new();
+static DISKS: OnceLock<Vec<PathBuf>> = OnceLock::new();
+
+/// Remember the `--os-root` selector for the rest of the command
+pub fn set_os_root(selector: String) {
+    let _ = OS_ROOT.set(selector);
+}
+
+/// Remember the `--disk` images for the rest of the command
+pub fn set_disks(paths: Vec<PathBuf>) {
+    let _ = DISKS.set(paths);
+}
+
+/// Attach the VM's further disks after the image a handle was given
+///
+/// They are opened read-only when the image is.
+pub fn add_disks(g: &mut Guestfs, readonly: bool) -> Result<()> {
+    for disk in DISKS.get().into_iter().flatten() {
+        g.add_drive_opts(disk, readonly, None)?;
+    }
+    Ok(())
+}
+
+/// Make a handle of the command's image put the chosen OS first
+pub fn select(g: &mut Guestfs) {
+    g.set_os_root(OS_ROOT.get().map(String::as_str));
+}
+
+/// Roots of the guest, the chosen OS first
+///
+/// An inspection that fails counts as no OS found, but a selector that
+/// matches none of the roots fails the command, listing the roots found.
+pub fn os_roots(g: &mut Guestfs) -> Result<Vec<String>> {
+    let Some(selector) = g.get_os_root().map(str::to_string) else {
+        return Ok(g.inspect_os().unwrap_or_default());
+    };
+
+    g.set_os_root(None);
+    let roots = g.inspect_os();
+    g.set_os_root(Some(&selector));
+
+    let mut roots = roots.unwrap_or_default();
+    select_os_root(&mut roots, &selector)?;
+    Ok(roots)
+}
@@ -1,12 +1,16 @@
 // SPDX-License-Identifier: LGPL-3.0-or-later
 //! TUI application state management
 
+use crate::cli::target;
 use anyhow::Result;
 use chrono::{DateTime, Local};
 use guestkit::guestfs::inspect_enhanced::{
     Database, FirewallInfo, HostEntry, LVMInfo, NetworkInterface, Package, PackageInfo,
     RAIDArray, SecurityInfo, SystemService, UserAccount, WebServer,
 };
+use guestkit::guestfs::preview::DEFAULT_PREVIEW_BYTES;
+use guestkit::guestfs::ContainerInventory;
+use guestkit::guestfs::WindowsScheduledTask;
 use guestkit::Guestfs;
 use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
@@ -82,6 +86,7 @@ pub enum View {
     Packages,
     Services,
     Databases,
+    Containers,
     WebServers,
     Security,
     Issues,
@@ -105,6 +110,7 @@ impl View {
             View::Packages => "Packages",
             View::Services => "Services",
             View::Databases => "Databases",
+            View::Containers => "Containers",
             View::WebServers => "WebServers",
             View::Security => "Security",
             View::Issues => "Issues",
@@ -128,6 +134,7 @@ impl View {
             View::Packages,
             View::Services,
             View::Databases,
+            View::Containers,
             View::WebServers,
             View::Security,
             View::Issues,
@@ -283,6 +290,7 @@ pub struct App {
     pub image_path: String,
     #[allow(dead_code)]
     pub image_path_buf: PathBuf,
+    pub os_type: String,
     pub os_name: String,
     pub os_version: String,
     pub hostname: String,
@@ -297,7 +305,9 @@ pub struct App {
 
     pub packages: PackageInfo,
     pub services: Vec<SystemService>,
+    pub scheduled_tasks: Vec<WindowsScheduledTask>,
     pub databases: Vec<Database>,
+    pub containers: ContainerInventory,
     pub web_servers: Vec<WebServer>,
     pub firewall: FirewallInfo,
     pub security: SecurityInfo,
@@ -334,6 +344,8 @@ impl App {
     pub fn new(image_path: &Path) -> Result<Self> {
         let mut guestfs = Guestfs::new()?;
         guestfs.add_drive_ro(image_path)?;
+        target::add_disks(&mut guestfs, true)?;
+        target::select(&mut guestfs);
         guestfs.launch()?;
 
         let roots = guestfs.inspect_os()?;
@@ -345,6 +357,8 @@ impl App {
         guestfs.mount_ro(root, "/")?;
 
         // Gather basic OS info
+        let os_type = guestfs.inspect_get_type(root)
+            .unwrap_or_else(|_| "unknown".to_string());
         let os_name = guestfs.inspect_get_product_name(root)
             .unwrap_or_else(|_| "Unknown".to_string());
         let os_version = guestfs.inspect_get_product_variant(root)
@@ -382,10 +396,30 @@ impl App {
                 packages: Vec::new(),
             });
 
-        let services = guestfs.inspect_systemd_services(root)
-            .unwrap_or_default();
+        // Windows services (drivers left out) are listed by start type
+        let (services, scheduled_tasks) = if os_type == "windows" {
+            let services = guestfs.inspect_windows_services(root)
+                .unwrap_or_default()
+                .into_iter()
+                .filter(|svc| !svc.service_type.ends_with("Driver"))
+                .map(|svc| SystemService {
+                    enabled: matches!(svc.start_type.as_str(), "Automatic" | "Boot" | "System"),
+                    state: svc.start_type.to_lowercase(),
+                    name: svc.name,
+                })
+                .collect();
+            let scheduled_tasks = guestfs.inspect_windows_scheduled_tasks(root)
+                .unwrap_or_default();
+            (services, scheduled_tasks)
+        } else {
+            let services = guestfs.inspect_systemd_services(root)
+                .unwrap_or_default();
+            (services, Vec::new())
+        };
         let databases = guestfs.inspect_databases(root)
             .unwrap_or_default();
+        let containers = guestfs.inspect_containers()
+            .unwrap_or_default();
         let web_servers = guestfs.inspect_web_servers(root)
             .unwrap_or_default();
         let firewall = guestfs.inspect_firewall(root)
@@ -443,6 +477,7 @@ impl App {
             "packages" => View::Packages,
             "services" => View::Services,
             "databases" => View::Databases,
+            "containers" => View::Containers,
             "webservers" => View::WebServers,
             "security" => View::Security,
             "issues" => View::Issues,
@@ -511,6 +546,7 @@ impl App {
 
             image_path: image_path.display().to_string(),
             image_path_buf: image_path.to_path_buf(),
+            os_type,
             os_name,
             os_version,
             hostname,
@@ -524,7 +560,9 @@ impl App {
             dns_servers,
             packages,
             services,
+            scheduled_tasks,
             databases,
+            containers,
             web_servers,
             firewall,
             security,
@@ -633,18 +671,10 @@ impl App {
                         }
                     }
 
-                    // Check file size - don't preview files > 1MB
-                    if let Ok(size) = guestfs.filesize(&path) {
-                        if size > 1024 * 1024 {
-                            self.show_notification(format!("File too large to preview ({} bytes)", size));
-                            return;
-                        }
-                    }
-
-                    // Read file content
-                    match guestfs.cat(&path) {
-                        Ok(content) => {
-                            self.file_preview_content = content;
+                    // Large files are previewed as their first and last lines
+                    match guestfs.preview_file(&path, DEFAULT_PREVIEW_BYTES) {
+                        Ok(window) => {
+                            self.file_preview_content = crate::cli::preview::preview_text(&window);
                             self.file_preview_path = path;
                             self.show_file_preview = true;
                         }
@@ -911,6 +941,20 @@ impl App {
                     }
                 }
             }
+            View::Containers => {
+                for (idx, image) in self.containers.images.iter().enumerate() {
+                    let name = image.tags.join(" ");
+                    let name = if self.search_case_sensitive {
+                        name
+                    } else {
+                        name.to_lowercase()
+                    };
+
+                    if name.contains(&query) || image.id.contains(&query) {
+                        self.search_results.push(idx);
+                    }
+                }
+            }
             View::WebServers => {
                 for (idx, ws) in self.web_servers.iter().enumerate() {
                     let name = if self.search_case_sensitive {
@@ -960,6 +1004,7 @@ impl App {
                 View::Network => self.network_interfaces.len(),
                 View::Users => self.users.len(),
                 View::Databases => self.databases.len(),
+                View::Containers => self.containers.images.len(),
                 View::WebServers => self.web_servers.len(),
                 View::Kernel => self.kernel_modules.len(),
                 View::Storage => self.fstab.len(),
@@ -1060,6 +1105,7 @@ impl App {
             View::Packages => "packages",
             View::Services => "services",
             View::Databases => "databases",
+            View::Containers => "containers",
             View::WebServers => "webservers",
             View::Security => "security",
             View::Issues => "issues",
@@ -1172,6 +1218,7 @@ impl App {
                     "services": self.services.len(),
                     "network_interfaces": self.network_interfaces.len(),
                     "databases": self.databases.len(),
+                    "container_images": self.containers.images.len(),
                     "web_servers": self.web_servers.len(),
                     "users": self.users.len(),
                 },
@@ -1215,6 +1262,12 @@ impl App {
                 "count": self.databases.len(),
                 "databases": self.databases,
             }),
+            View::Containers => json!({
+                "view": "containers",
+                "count": self.containers.images.len(),
+                "images": self.containers.images,
+                "containers": self.containers.containers,
+            }),
             View::WebServers => json!({
                 "view": "webservers",
                 "count": self.web_servers.len(),
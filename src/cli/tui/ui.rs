@@ -102,6 +102,7 @@ fn draw_header(f: &mut Frame, area: Rect, app: &App) {
         View::Packages => ("📦", "Installed Packages"),
         View::Services => ("⚙️ ", "System Services"),
         View::Databases => ("🗄️ ", "Database Installations"),
+        View::Containers => ("🐳", "Container Images"),
         View::WebServers => ("🌐", "Web Server Installations"),
         View::Security => ("🔒", "Security Features"),
         View::Issues => ("⚠️ ", "Security Issues & Findings"),
@@ -195,6 +196,7 @@ fn draw_tabs(f: &mut Frame, area: Rect, app: &App) {
             View::Packages => Some(app.packages.package_count),
             View::Services => Some(app.services.len()),
             View::Databases => Some(app.databases.len()),
+            View::Containers => Some(app.containers.images.len()),
             View::WebServers => Some(app.web_servers.len()),
             View::Security => None,
             View::Issues => {
@@ -244,6 +246,7 @@ fn draw_content(f: &mut Frame, area: Rect, app: &App) {
         View::Packages => views::packages::draw(f, area, app),
         View::Services => views::services::draw(f, area, app),
         View::Databases => views::databases::draw(f, area, app),
+        View::Containers => views::containers::draw(f, area, app),
         View::WebServers => views::webservers::draw(f, area, app),
         View::Security => views::security::draw(f, area, app),
         View::Issues => views::issues::draw(f, area, app),
@@ -760,6 +763,7 @@ fn draw_detail_overlay(f: &mut Frame, app: &App) {
         View::Packages => generate_packages_details(app),
         View::Services => generate_services_details(app),
         View::Databases => generate_databases_details(app),
+        View::Containers => generate_containers_details(app),
         View::WebServers => generate_webservers_details(app),
         View::Security => generate_security_details(app),
         View::Issues => generate_issues_details(app),
@@ -1118,6 +1122,43 @@ fn generate_databases_details(app: &App) -> Vec<Line<'static>> {
     ]
 }
 
+fn generate_containers_details(app: &App) -> Vec<Line<'static>> {
+    let running = app.containers.containers.iter()
+        .filter(|c| c.state.as_deref() == Some("running"))
+        .count();
+    let mut runtimes: Vec<&str> = app.containers.images.iter()
+        .map(|image| image.runtime.as_str())
+        .collect();
+    runtimes.sort();
+    runtimes.dedup();
+    vec![
+        Line::from(vec![
+            Span::styled("Container Images", Style::default().fg(LIGHT_ORANGE).add_modifier(Modifier::BOLD | Modifier::UNDERLINED))
+        ]),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("Total Images:     ", Style::default().fg(LIGHT_ORANGE)),
+            Span::styled(format!("{}", app.containers.images.len()), Style::default().fg(SUCCESS_COLOR)),
+        ]),
+        Line::from(vec![
+            Span::styled("Containers:       ", Style::default().fg(LIGHT_ORANGE)),
+            Span::styled(format!("{}", app.containers.containers.len()), Style::default().fg(SUCCESS_COLOR)),
+        ]),
+        Line::from(vec![
+            Span::styled("Running:          ", Style::default().fg(LIGHT_ORANGE)),
+            Span::styled(format!("{}", running), Style::default().fg(WARNING_COLOR)),
+        ]),
+        Line::from(vec![
+            Span::styled("Runtimes:         ", Style::default().fg(LIGHT_ORANGE)),
+            Span::styled(runtimes.join(", "), Style::default().fg(TEXT_COLOR)),
+        ]),
+        Line::from(""),
+        Line::from(vec![
+            Span::styled("Press ESC or Enter to close", Style::default().fg(DARK_ORANGE).add_modifier(Modifier::ITALIC))
+        ]),
+    ]
+}
+
 fn generate_webservers_details(app: &App) -> Vec<Line<'static>> {
     let enabled_count = app.web_servers.iter().filter(|ws| ws.enabled).count();
     vec![
@@ -1542,11 +1583,7 @@ fn draw_file_preview(f: &mut Frame, app: &App) {
                     Style::default().fg(LIGHT_ORANGE),
                 ),
                 Span::styled(
-                    if line.len() > 120 {
-                        format!("{}...", &line[..120])
-                    } else {
-                        line.to_string()
-                    },
+                    crate::cli::output::truncate_display(line, 123),
                     Style::default().fg(TEXT_COLOR),
                 ),
             ])
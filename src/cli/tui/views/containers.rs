@@ -0,0 +1,165 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Containers view - Container images and containers stored in the guest
+
+use crate::cli::output::format_size;
+use crate::cli::tui::app::App;
+use crate::cli::tui::ui::{BORDER_COLOR, LIGHT_ORANGE, ORANGE, SUCCESS_COLOR, TEXT_COLOR, WARNING_COLOR};
+use guestkit::guestfs::ContainerImage;
+use ratatui::{
+    layout::{Constraint, Direction, Layout, Rect},
+    style::{Modifier, Style},
+    text::{Line, Span},
+    widgets::{Block, Borders, List, ListItem, Paragraph},
+    Frame,
+};
+
+pub fn draw(f: &mut Frame, area: Rect, app: &App) {
+    if app.containers.is_empty() {
+        let empty = Paragraph::new("⚠️  No Docker, Podman or containerd storage found")
+            .block(Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(BORDER_COLOR))
+                .title(" 🐳 Containers ")
+                .title_style(Style::default().fg(ORANGE).add_modifier(Modifier::BOLD)))
+            .style(Style::default().fg(TEXT_COLOR));
+        f.render_widget(empty, area);
+        return;
+    }
+
+    // Split area into images and containers
+    let chunks = Layout::default()
+        .direction(Direction::Vertical)
+        .constraints([
+            Constraint::Percentage(60), // Image list
+            Constraint::Percentage(40), // Container list
+        ])
+        .split(area);
+
+    draw_image_list(f, chunks[0], app);
+    draw_container_list(f, chunks[1], app);
+}
+
+fn image_matches(image: &ContainerImage, query: &str) -> bool {
+    image.tags.iter().chain(&image.digests).any(|name| name.to_lowercase().contains(query))
+        || image.id.contains(query)
+        || image.runtime.contains(query)
+}
+
+fn draw_image_list(f: &mut Frame, area: Rect, app: &App) {
+    let query = app.search_query.to_lowercase();
+    let filtered_images: Vec<_> = if app.is_searching() && !query.is_empty() {
+        app.containers.images.iter().filter(|image| image_matches(image, &query)).collect()
+    } else {
+        app.containers.images.iter().collect()
+    };
+
+    let items: Vec<ListItem> = filtered_images
+        .iter()
+        .skip(app.scroll_offset)
+        .take(area.height.saturating_sub(2) as usize)
+        .map(|image| {
+            let id = image.id.trim_start_matches("sha256:");
+            let platform = match (&image.os, &image.architecture) {
+                (Some(os), Some(arch)) => format!("{}/{}", os, arch),
+                _ => "-".to_string(),
+            };
+            let in_use = app.containers.containers_of(image).count();
+
+            ListItem::new(Line::from(vec![
+                Span::styled(
+                    format!("{:10} ", image.runtime),
+                    Style::default().fg(LIGHT_ORANGE)
+                ),
+                Span::styled(
+                    format!("{:45} ", image.name()),
+                    Style::default().fg(SUCCESS_COLOR).add_modifier(Modifier::BOLD)
+                ),
+                Span::styled(
+                    format!("{:12} ", &id[..id.len().min(12)]),
+                    Style::default().fg(TEXT_COLOR)
+                ),
+                Span::styled(
+                    format!("{:13} ", platform),
+                    Style::default().fg(TEXT_COLOR)
+                ),
+                Span::styled(
+                    format!("{:>10} ", format_size(image.size())),
+                    Style::default().fg(TEXT_COLOR)
+                ),
+                Span::styled(
+                    if in_use > 0 { format!("{} containers", in_use) } else { String::new() },
+                    Style::default().fg(WARNING_COLOR)
+                ),
+            ]))
+        })
+        .collect();
+
+    // Calculate scroll position
+    let visible_items = area.height.saturating_sub(2) as usize;
+    let total_items = filtered_images.len();
+    let scroll_pct = if total_items > 0 {
+        ((app.scroll_offset as f32 / total_items.max(1) as f32) * 100.0) as u16
+    } else {
+        0
+    };
+
+    let scroll_indicator = if total_items > visible_items {
+        format!(" 📜 {}% ", scroll_pct)
+    } else {
+        String::new()
+    };
+
+    let list = List::new(items)
+        .block(Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(BORDER_COLOR))
+            .title(format!(" 🐳 Images • {} total{} ",
+                filtered_images.len(), scroll_indicator))
+            .title_style(Style::default().fg(ORANGE).add_modifier(Modifier::BOLD)));
+
+    f.render_widget(list, area);
+}
+
+fn draw_container_list(f: &mut Frame, area: Rect, app: &App) {
+    let items: Vec<ListItem> = app.containers.containers
+        .iter()
+        .take(area.height.saturating_sub(2) as usize)
+        .map(|container| {
+            let (icon, state_color) = match container.state.as_deref() {
+                Some("running") => ("▶", SUCCESS_COLOR),
+                Some("paused") => ("⏸", WARNING_COLOR),
+                Some(_) => ("■", TEXT_COLOR),
+                None => ("•", TEXT_COLOR),
+            };
+
+            ListItem::new(Line::from(vec![
+                Span::styled(format!("{} ", icon), Style::default().fg(state_color)),
+                Span::styled(
+                    format!("{:10} ", container.runtime),
+                    Style::default().fg(LIGHT_ORANGE)
+                ),
+                Span::styled(
+                    format!("{:30} ", container.name),
+                    Style::default().fg(state_color).add_modifier(Modifier::BOLD)
+                ),
+                Span::styled(
+                    format!("{:45} ", container.image),
+                    Style::default().fg(TEXT_COLOR)
+                ),
+                Span::styled(
+                    container.created.clone().unwrap_or_default(),
+                    Style::default().fg(TEXT_COLOR)
+                ),
+            ]))
+        })
+        .collect();
+
+    let list = List::new(items)
+        .block(Block::default()
+            .borders(Borders::ALL)
+            .border_style(Style::default().fg(BORDER_COLOR))
+            .title(format!(" 📦 Containers • {} total ", app.containers.containers.len()))
+            .title_style(Style::default().fg(ORANGE).add_modifier(Modifier::BOLD)));
+
+    f.render_widget(list, area);
+}
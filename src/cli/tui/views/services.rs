@@ -1,5 +1,5 @@
 // SPDX-License-Identifier: LGPL-3.0-or-later
-//! Services view - Systemd services viewer
+//! Services view - Systemd services, or Windows services and scheduled tasks
 
 use crate::cli::tui::app::App;
 use crate::cli::tui::ui::{BORDER_COLOR, ERROR_COLOR, INFO_COLOR, LIGHT_ORANGE, ORANGE, SUCCESS_COLOR, TEXT_COLOR, WARNING_COLOR};
@@ -11,30 +11,48 @@ use ratatui::{
     Frame,
 };
 
+/// Title of the service list for the inspected OS
+fn services_title(app: &App) -> &'static str {
+    if app.os_type == "windows" {
+        "Windows Services"
+    } else {
+        "Systemd Services"
+    }
+}
+
 pub fn draw(f: &mut Frame, area: Rect, app: &App) {
-    if app.services.is_empty() {
-        let empty = Paragraph::new("⚠️  No systemd services found")
+    if app.services.is_empty() && app.scheduled_tasks.is_empty() {
+        let empty = Paragraph::new(format!("⚠️  No {} found", services_title(app).to_lowercase()))
             .block(Block::default()
                 .borders(Borders::ALL)
                 .border_style(Style::default().fg(BORDER_COLOR))
-                .title(" ⚙️  Systemd Services ")
+                .title(format!(" ⚙️  {} ", services_title(app)))
                 .title_style(Style::default().fg(ORANGE).add_modifier(Modifier::BOLD)))
             .style(Style::default().fg(TEXT_COLOR));
         f.render_widget(empty, area);
         return;
     }
 
-    // Split area into summary and list sections
+    // Split area into summary, list and scheduled task sections
+    let tasks_height = if app.scheduled_tasks.is_empty() {
+        0
+    } else {
+        (app.scheduled_tasks.len() as u16 + 4).min(area.height / 3)
+    };
     let chunks = Layout::default()
         .direction(Direction::Vertical)
         .constraints([
-            Constraint::Length(8),  // Summary gauges
-            Constraint::Min(0),     // Service list
+            Constraint::Length(8),            // Summary gauges
+            Constraint::Min(0),               // Service list
+            Constraint::Length(tasks_height), // Scheduled tasks
         ])
         .split(area);
 
     draw_service_summary(f, chunks[0], app);
     draw_service_list(f, chunks[1], app);
+    if tasks_height > 0 {
+        draw_scheduled_tasks(f, chunks[2], app);
+    }
 }
 
 fn draw_service_summary(f: &mut Frame, area: Rect, app: &App) {
@@ -84,6 +102,29 @@ fn draw_service_summary(f: &mut Frame, area: Rect, app: &App) {
 
     f.render_widget(enabled_gauge, gauge_chunks[1]);
 
+    // Offline Windows images have no running state; show task status instead
+    if app.os_type == "windows" {
+        let enabled_tasks = app.scheduled_tasks.iter().filter(|t| t.enabled).count();
+        let tasks_pct = if app.scheduled_tasks.is_empty() {
+            0
+        } else {
+            (enabled_tasks as f64 / app.scheduled_tasks.len() as f64 * 100.0) as u16
+        };
+
+        let tasks_gauge = Gauge::default()
+            .block(Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(BORDER_COLOR))
+                .title(" Scheduled Tasks "))
+            .gauge_style(Style::default().fg(INFO_COLOR))
+            .percent(tasks_pct)
+            .label(format!("{} enabled • {} disabled ({}%)",
+                enabled_tasks, app.scheduled_tasks.len() - enabled_tasks, tasks_pct));
+
+        f.render_widget(tasks_gauge, gauge_chunks[2]);
+        return;
+    }
+
     // Running/Stopped gauge
     let running_gauge = Gauge::default()
         .block(Block::default()
@@ -144,7 +185,7 @@ fn draw_service_list_view(f: &mut Frame, area: Rect, app: &App) {
             // Color the state based on its value
             let state_color = match svc.state.as_str() {
                 "running" | "active" => SUCCESS_COLOR,
-                "stopped" | "inactive" | "failed" => ERROR_COLOR,
+                "stopped" | "inactive" | "failed" | "disabled" => ERROR_COLOR,
                 _ => WARNING_COLOR,
             };
 
@@ -232,8 +273,8 @@ fn draw_service_list_view(f: &mut Frame, area: Rect, app: &App) {
         .block(Block::default()
             .borders(Borders::ALL)
             .border_style(Style::default().fg(BORDER_COLOR))
-            .title(format!(" ⚙️  Systemd Services • {} showing • {} enabled • {} running{}{}{}{} ",
-                filtered_indices.len(), enabled_count, running_count, scroll_indicator, multiselect_indicator, filter_indicator, sort_indicator))
+            .title(format!(" ⚙️  {} • {} showing • {} enabled • {} running{}{}{}{} ",
+                services_title(app), filtered_indices.len(), enabled_count, running_count, scroll_indicator, multiselect_indicator, filter_indicator, sort_indicator))
             .title_style(Style::default().fg(ORANGE).add_modifier(Modifier::BOLD)));
 
     f.render_widget(list, area);
@@ -289,7 +330,7 @@ fn draw_service_table_view(f: &mut Frame, area: Rect, app: &App) {
             // State color
             let state_color = match svc.state.as_str() {
                 "running" | "active" => SUCCESS_COLOR,
-                "stopped" | "inactive" | "failed" => ERROR_COLOR,
+                "stopped" | "inactive" | "failed" | "disabled" => ERROR_COLOR,
                 _ => WARNING_COLOR,
             };
 
@@ -384,3 +425,68 @@ fn draw_service_table_view(f: &mut Frame, area: Rect, app: &App) {
 
     f.render_widget(table, area);
 }
+
+fn draw_scheduled_tasks(f: &mut Frame, area: Rect, app: &App) {
+    let header = Row::new(vec![
+        Cell::from(Span::styled("Status", Style::default().fg(ORANGE).add_modifier(Modifier::BOLD))),
+        Cell::from(Span::styled("Task", Style::default().fg(ORANGE).add_modifier(Modifier::BOLD))),
+        Cell::from(Span::styled("Triggers", Style::default().fg(ORANGE).add_modifier(Modifier::BOLD))),
+        Cell::from(Span::styled("Run As", Style::default().fg(ORANGE).add_modifier(Modifier::BOLD))),
+        Cell::from(Span::styled("Command", Style::default().fg(ORANGE).add_modifier(Modifier::BOLD))),
+    ])
+    .height(1)
+    .bottom_margin(1);
+
+    let rows: Vec<Row> = app
+        .scheduled_tasks
+        .iter()
+        .take(area.height.saturating_sub(4) as usize)
+        .map(|task| {
+            let (status_symbol, status_color) = if task.enabled {
+                ("🟢", SUCCESS_COLOR)
+            } else {
+                ("⚫", TEXT_COLOR)
+            };
+            let run_as = match (&task.run_as, task.elevated) {
+                (Some(account), true) => format!("{} (elevated)", account),
+                (Some(account), false) => account.clone(),
+                (None, _) => "-".to_string(),
+            };
+
+            Row::new(vec![
+                Cell::from(Span::styled(status_symbol, Style::default().fg(status_color))),
+                Cell::from(Span::styled(&task.path, Style::default().fg(LIGHT_ORANGE).add_modifier(Modifier::BOLD))),
+                Cell::from(Span::styled(task.triggers.join(", "), Style::default().fg(INFO_COLOR))),
+                Cell::from(Span::styled(run_as, Style::default().fg(TEXT_COLOR))),
+                Cell::from(Span::styled(task.command.as_deref().unwrap_or("-"), Style::default().fg(TEXT_COLOR))),
+            ])
+        })
+        .collect();
+
+    let enabled_count = app.scheduled_tasks.iter().filter(|t| t.enabled).count();
+
+    let widths = [
+        Constraint::Length(6),      // Status
+        Constraint::Percentage(35), // Task path
+        Constraint::Percentage(15), // Triggers
+        Constraint::Percentage(15), // Run as
+        Constraint::Percentage(35), // Command
+    ];
+
+    let table = Table::new(rows, widths)
+        .header(header)
+        .block(
+            Block::default()
+                .borders(Borders::ALL)
+                .border_style(Style::default().fg(BORDER_COLOR))
+                .title(format!(
+                    " ⏰ Scheduled Tasks • {} total • {} enabled ",
+                    app.scheduled_tasks.len(),
+                    enabled_count
+                ))
+                .title_style(Style::default().fg(ORANGE).add_modifier(Modifier::BOLD)),
+        )
+        .column_spacing(2);
+
+    f.render_widget(table, area);
+}
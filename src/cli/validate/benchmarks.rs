@@ -1,13 +1,21 @@
 // SPDX-License-Identifier: LGPL-3.0-or-later
 //! Industry benchmark policies (CIS, NIST, etc.)
+//!
+//! The CIS benchmarks for current Ubuntu and RHEL releases are bundled as
+//! policy files under `cis/`, with rule ids following the benchmark's
+//! section numbers and each rule tagged with its profile level.
 
 use super::policy::{Policy, PolicyRule, RuleType};
+use std::collections::BTreeMap;
 
 /// Supported industry benchmarks
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum Benchmark {
     CisUbuntu2004,
+    CisUbuntu2204,
+    CisUbuntu2404,
     CisRhel8,
+    CisRhel9,
     NistCsf,
     PciDss,
     Hipaa,
@@ -17,7 +25,10 @@ impl Benchmark {
     pub fn from_str(s: &str) -> Option<Self> {
         match s.to_lowercase().as_str() {
             "cis-ubuntu-20.04" | "cis-ubuntu" => Some(Self::CisUbuntu2004),
+            "cis-ubuntu-22.04" => Some(Self::CisUbuntu2204),
+            "cis-ubuntu-24.04" => Some(Self::CisUbuntu2404),
             "cis-rhel-8" | "cis-rhel" => Some(Self::CisRhel8),
+            "cis-rhel-9" => Some(Self::CisRhel9),
             "nist-csf" | "nist" => Some(Self::NistCsf),
             "pci-dss" | "pci" => Some(Self::PciDss),
             "hipaa" => Some(Self::Hipaa),
@@ -28,7 +39,10 @@ impl Benchmark {
     pub fn to_policy(self) -> Policy {
         match self {
             Self::CisUbuntu2004 => cis_ubuntu_2004_policy(),
-            Self::CisRhel8 => cis_rhel8_policy(),
+            Self::CisUbuntu2204 => bundled(include_str!("cis/ubuntu-22.04.yaml")),
+            Self::CisUbuntu2404 => bundled(include_str!("cis/ubuntu-24.04.yaml")),
+            Self::CisRhel8 => bundled(include_str!("cis/rhel-8.yaml")),
+            Self::CisRhel9 => bundled(include_str!("cis/rhel-9.yaml")),
             Self::NistCsf => nist_csf_policy(),
             Self::PciDss => pci_dss_policy(),
             Self::Hipaa => hipaa_policy(),
@@ -41,12 +55,16 @@ fn cis_ubuntu_2004_policy() -> Policy {
         name: "CIS Ubuntu 20.04 Benchmark".to_string(),
         version: "1.1.0".to_string(),
         description: "Center for Internet Security Ubuntu 20.04 LTS Benchmark".to_string(),
+        extends: Vec::new(),
+        variables: BTreeMap::new(),
         rules: vec![
             PolicyRule {
                 id: "CIS-1.1.1.1".to_string(),
                 name: "Ensure mounting of cramfs filesystems is disabled".to_string(),
                 description: "The cramfs filesystem type is a compressed read-only Linux filesystem".to_string(),
                 severity: "low".to_string(),
+                weight: None,
+                level: None,
                 rule_type: RuleType::FileNotExists {
                     path: "/etc/modprobe.d/cramfs.conf".to_string(),
                 },
@@ -57,6 +75,8 @@ fn cis_ubuntu_2004_policy() -> Policy {
                 name: "Ensure permissions on bootloader config are configured".to_string(),
                 description: "Grub configuration must have restricted permissions".to_string(),
                 severity: "high".to_string(),
+                weight: None,
+                level: None,
                 rule_type: RuleType::FilePermissions {
                     path: "/boot/grub/grub.cfg".to_string(),
                     mode: "400".to_string(),
@@ -68,6 +88,8 @@ fn cis_ubuntu_2004_policy() -> Policy {
                 name: "Ensure permissions on /etc/ssh/sshd_config are configured".to_string(),
                 description: "The /etc/ssh/sshd_config file must be owned by root with 600 permissions".to_string(),
                 severity: "high".to_string(),
+                weight: None,
+                level: None,
                 rule_type: RuleType::FilePermissions {
                     path: "/etc/ssh/sshd_config".to_string(),
                     mode: "600".to_string(),
@@ -79,6 +101,8 @@ fn cis_ubuntu_2004_policy() -> Policy {
                 name: "Ensure SSH root login is disabled".to_string(),
                 description: "The PermitRootLogin parameter should be set to no".to_string(),
                 severity: "critical".to_string(),
+                weight: None,
+                level: None,
                 rule_type: RuleType::FileContains {
                     path: "/etc/ssh/sshd_config".to_string(),
                     pattern: "PermitRootLogin no".to_string(),
@@ -89,35 +113,9 @@ fn cis_ubuntu_2004_policy() -> Policy {
     }
 }
 
-fn cis_rhel8_policy() -> Policy {
-    Policy {
-        name: "CIS Red Hat Enterprise Linux 8 Benchmark".to_string(),
-        version: "2.0.0".to_string(),
-        description: "Center for Internet Security RHEL 8 Benchmark".to_string(),
-        rules: vec![
-            PolicyRule {
-                id: "CIS-1.1.1.1".to_string(),
-                name: "Ensure mounting of cramfs filesystems is disabled".to_string(),
-                description: "The cramfs filesystem should be disabled".to_string(),
-                severity: "low".to_string(),
-                rule_type: RuleType::FileNotExists {
-                    path: "/etc/modprobe.d/cramfs.conf".to_string(),
-                },
-                remediation: Some("echo 'install cramfs /bin/true' > /etc/modprobe.d/cramfs.conf".to_string()),
-            },
-            PolicyRule {
-                id: "CIS-1.5.1".to_string(),
-                name: "Ensure permissions on bootloader config are configured".to_string(),
-                severity: "high".to_string(),
-                description: "Bootloader configuration must have restricted permissions".to_string(),
-                rule_type: RuleType::FilePermissions {
-                    path: "/boot/grub2/grub.cfg".to_string(),
-                    mode: "600".to_string(),
-                },
-                remediation: Some("chmod 600 /boot/grub2/grub.cfg".to_string()),
-            },
-        ],
-    }
+/// Parse a policy file shipped with guestkit
+fn bundled(yaml: &str) -> Policy {
+    Policy::from_yaml(yaml).expect("bundled benchmark policy is valid")
 }
 
 fn nist_csf_policy() -> Policy {
@@ -125,12 +123,16 @@ fn nist_csf_policy() -> Policy {
         name: "NIST Cybersecurity Framework".to_string(),
         version: "1.1".to_string(),
         description: "NIST CSF security controls".to_string(),
+        extends: Vec::new(),
+        variables: BTreeMap::new(),
         rules: vec![
             PolicyRule {
                 id: "NIST-PR.AC-1".to_string(),
                 name: "Identities and credentials are managed".to_string(),
                 description: "User accounts and credentials are properly managed".to_string(),
                 severity: "high".to_string(),
+                weight: None,
+                level: None,
                 rule_type: RuleType::FileExists {
                     path: "/etc/passwd".to_string(),
                 },
@@ -141,6 +143,8 @@ fn nist_csf_policy() -> Policy {
                 name: "Data-at-rest is protected".to_string(),
                 description: "Ensure data at rest protection mechanisms are in place".to_string(),
                 severity: "high".to_string(),
+                weight: None,
+                level: None,
                 rule_type: RuleType::PackageInstalled {
                     package: "cryptsetup".to_string(),
                 },
@@ -155,12 +159,16 @@ fn pci_dss_policy() -> Policy {
         name: "PCI DSS Requirements".to_string(),
         version: "3.2.1".to_string(),
         description: "Payment Card Industry Data Security Standard".to_string(),
+        extends: Vec::new(),
+        variables: BTreeMap::new(),
         rules: vec![
             PolicyRule {
                 id: "PCI-2.2.2".to_string(),
                 name: "Enable only necessary services".to_string(),
                 description: "Disable all unnecessary services and protocols".to_string(),
                 severity: "high".to_string(),
+                weight: None,
+                level: None,
                 rule_type: RuleType::PackageForbidden {
                     package: "telnet".to_string(),
                 },
@@ -171,6 +179,8 @@ fn pci_dss_policy() -> Policy {
                 name: "Configure security parameters".to_string(),
                 description: "Security parameters must be configured to prevent misuse".to_string(),
                 severity: "critical".to_string(),
+                weight: None,
+                level: None,
                 rule_type: RuleType::FileContains {
                     path: "/etc/ssh/sshd_config".to_string(),
                     pattern: "PermitRootLogin no".to_string(),
@@ -186,12 +196,16 @@ fn hipaa_policy() -> Policy {
         name: "HIPAA Security Rule".to_string(),
         version: "1.0".to_string(),
         description: "Health Insurance Portability and Accountability Act security controls".to_string(),
+        extends: Vec::new(),
+        variables: BTreeMap::new(),
         rules: vec![
             PolicyRule {
                 id: "HIPAA-164.308".to_string(),
                 name: "Access Control".to_string(),
                 description: "Implement technical policies and procedures for systems that maintain ePHI".to_string(),
                 severity: "critical".to_string(),
+                weight: None,
+                level: None,
                 rule_type: RuleType::FileExists {
                     path: "/etc/passwd".to_string(),
                 },
@@ -202,6 +216,8 @@ fn hipaa_policy() -> Policy {
                 name: "Encryption and Decryption".to_string(),
                 description: "Implement a mechanism to encrypt and decrypt ePHI".to_string(),
                 severity: "critical".to_string(),
+                weight: None,
+                level: None,
                 rule_type: RuleType::PackageInstalled {
                     package: "cryptsetup".to_string(),
                 },
@@ -210,3 +226,48 @@ fn hipaa_policy() -> Policy {
         ],
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::validate::CompareOp;
+    use std::collections::HashSet;
+
+    #[test]
+    fn test_bundled_cis_benchmarks() {
+        for name in ["cis-ubuntu-22.04", "cis-ubuntu-24.04", "cis-rhel-8", "cis-rhel-9"] {
+            let policy = Benchmark::from_str(name).unwrap().to_policy();
+            assert!(policy.rules.len() >= 180, "{}: {} rules", name, policy.rules.len());
+
+            let ids: HashSet<_> = policy.rules.iter().map(|r| r.id.as_str()).collect();
+            assert_eq!(ids.len(), policy.rules.len(), "{}: duplicate rule ids", name);
+            assert!(policy
+                .rules
+                .iter()
+                .all(|r| matches!(r.level, Some(1 | 2)) && r.remediation.is_some()));
+
+            let level1 = policy.clone().at_level(1);
+            assert!(!level1.rules.is_empty() && level1.rules.len() < policy.rules.len());
+
+            for rule in &policy.rules {
+                check_patterns(&rule.rule_type);
+            }
+        }
+    }
+
+    /// Every regex and glob in a bundled rule must compile
+    fn check_patterns(rule_type: &RuleType) {
+        match rule_type {
+            RuleType::FileMatches { path, pattern } => {
+                assert!(glob::Pattern::new(path).is_ok(), "{}", path);
+                assert!(regex::Regex::new(pattern).is_ok(), "{}", pattern);
+            }
+            RuleType::ConfigValue { op, value, .. } if *op == CompareOp::Matches => {
+                assert!(regex::Regex::new(value).is_ok(), "{}", value);
+            }
+            RuleType::And { rules } | RuleType::Or { rules } => rules.iter().for_each(check_patterns),
+            RuleType::Not { rule } => check_patterns(rule),
+            _ => {}
+        }
+    }
+}
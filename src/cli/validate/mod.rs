@@ -4,14 +4,18 @@
 pub mod policy;
 pub mod rules;
 pub mod benchmarks;
+pub mod rego;
 
+use crate::cli::plain;
+use crate::cli::target;
 use anyhow::Result;
 use guestkit::Guestfs;
 use serde::{Deserialize, Serialize};
 use std::path::Path;
 
-pub use policy::{Policy, PolicyRule, RuleType};
+pub use policy::{CompareOp, Policy, PolicyRule, RuleType};
 pub use benchmarks::Benchmark;
+pub use rego::{ImageFacts, RegoPolicy};
 
 /// Validation result for a single rule
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -21,9 +25,16 @@ pub struct ValidationResult {
     pub status: ValidationStatus,
     pub message: String,
     pub severity: String,
+    /// Share of the compliance score
+    #[serde(default = "default_weight")]
+    pub weight: f64,
     pub remediation: Option<String>,
 }
 
+fn default_weight() -> f64 {
+    1.0
+}
+
 /// Validation status
 #[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
 pub enum ValidationStatus {
@@ -61,6 +72,8 @@ impl ValidationStatus {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ValidationReport {
     pub image_path: String,
+    #[serde(default)]
+    pub root: String,
     pub policy_name: String,
     pub timestamp: String,
     pub results: Vec<ValidationResult>,
@@ -88,8 +101,18 @@ impl ValidationSummary {
         let skipped = results.iter().filter(|r| r.status == ValidationStatus::Skip).count();
         let errors = results.iter().filter(|r| r.status == ValidationStatus::Error).count();
 
-        let compliance_score = if total > 0 {
-            (passed as f64 / (total - skipped) as f64) * 100.0
+        // Weighted share of the rules that could be checked
+        let weight = |pass_only: bool| {
+            results
+                .iter()
+                .filter(|r| r.status != ValidationStatus::Skip)
+                .filter(|r| !pass_only || r.status == ValidationStatus::Pass)
+                .map(|r| r.weight)
+                .sum::<f64>()
+        };
+        let checked = weight(false);
+        let compliance_score = if checked > 0.0 {
+            weight(true) / checked * 100.0
         } else {
             0.0
         };
@@ -107,62 +130,114 @@ impl ValidationSummary {
 }
 
 /// Validate disk image against policy
+///
+/// One report per operating system, or only for the one chosen with
+/// `--os-root`.
 pub fn validate_image<P: AsRef<Path>>(
     image_path: P,
     policy: &Policy,
     verbose: bool,
-) -> Result<ValidationReport> {
-    let image_path_str = image_path.as_ref().display().to_string();
+) -> Result<Vec<ValidationReport>> {
+    if verbose {
+        println!("{}Validating: {}", plain::text("🔍 "), image_path.as_ref().display());
+        println!("{}Policy: {}", plain::text("📋 "), policy.name);
+    }
+
+    for_each_root(&image_path, verbose, |g, root| {
+        // Run validation rules
+        let mut results = Vec::new();
+
+        for rule in &policy.rules {
+            if verbose {
+                println!("  Checking: {}", rule.name);
+            }
+
+            let result = validate_rule(g, root, rule)?;
+            results.push(result);
+        }
 
+        Ok(report(&image_path, root, &policy.name, results))
+    })
+}
+
+/// Validate disk image against Rego policies, with the image facts as input
+pub fn validate_image_rego<P: AsRef<Path>>(
+    image_path: P,
+    policy: &RegoPolicy,
+    verbose: bool,
+) -> Result<Vec<ValidationReport>> {
     if verbose {
-        println!("🔍 Validating: {}", image_path_str);
-        println!("📋 Policy: {}", policy.name);
+        println!("{}Validating: {}", plain::text("🔍 "), image_path.as_ref().display());
+        println!("{}Rego policy: {}", plain::text("📋 "), policy.name());
     }
 
+    for_each_root(&image_path, verbose, |g, root| {
+        let facts = rego::collect_facts(g, root)?;
+        let results = policy.evaluate(&facts)?;
+        Ok(report(&image_path, root, policy.name(), results))
+    })
+}
+
+/// Facts of each operating system, as Rego policies see them
+pub fn image_facts<P: AsRef<Path>>(image_path: P, verbose: bool) -> Result<Vec<ImageFacts>> {
+    for_each_root(&image_path, verbose, rego::collect_facts)
+}
+
+/// Run `f` with each selected operating system mounted read-only
+fn for_each_root<P, T, F>(image_path: P, verbose: bool, mut f: F) -> Result<Vec<T>>
+where
+    P: AsRef<Path>,
+    F: FnMut(&mut Guestfs, &str) -> Result<T>,
+{
     // Initialize guestfs
     let mut g = Guestfs::new()?;
     g.add_drive_opts(&image_path, true, None)?;
+    target::add_disks(&mut g, true)?;
+    target::select(&mut g);
     g.launch()?;
 
     // Inspect OS
-    let roots = g.inspect_os()?;
+    let roots = g.inspect_os_selected()?;
     if roots.is_empty() {
         anyhow::bail!("No operating systems found in disk image");
     }
 
-    let root = &roots[0];
-
-    // Mount filesystems
-    let mountpoints = g.inspect_get_mountpoints(root)?;
-    for (mp, dev) in mountpoints {
-        let _ = g.mount(&dev, &mp);
-    }
-
-    // Run validation rules
-    let mut results = Vec::new();
-
-    for rule in &policy.rules {
-        if verbose {
-            println!("  Checking: {}", rule.name);
+    let mut outputs = Vec::new();
+    for root in &roots {
+        if verbose && roots.len() > 1 {
+            println!("{}OS root: {}", plain::text("💽 "), root);
         }
 
-        let result = validate_rule(&mut g, root, rule)?;
-        results.push(result);
+        // Mount filesystems
+        g.umount_all()?;
+        g.mount_os_ro(root)?;
+
+        outputs.push(f(&mut g, root)?);
     }
 
     // Shutdown guestfs
     g.shutdown()?;
 
+    Ok(outputs)
+}
+
+fn report<P: AsRef<Path>>(
+    image_path: P,
+    root: &str,
+    policy_name: &str,
+    results: Vec<ValidationResult>,
+) -> ValidationReport {
     // Calculate summary
     let summary = ValidationSummary::new(&results);
 
-    Ok(ValidationReport {
-        image_path: image_path_str,
-        policy_name: policy.name.clone(),
+    ValidationReport {
+        image_path: image_path.as_ref().display().to_string(),
+        root: root.to_string(),
+        policy_name: policy_name.to_string(),
         timestamp: chrono::Utc::now().to_rfc3339(),
         results,
         summary,
-    })
+    }
 }
 
 /// Validate a single rule
@@ -171,7 +246,28 @@ fn validate_rule(
     root: &str,
     rule: &PolicyRule,
 ) -> Result<ValidationResult> {
-    let status = match &rule.rule_type {
+    let status = evaluate(g, root, &rule.rule_type)?;
+
+    let message = if status == ValidationStatus::Pass {
+        format!("{} - Check passed", rule.name)
+    } else {
+        format!("{} - Check failed", rule.name)
+    };
+
+    Ok(ValidationResult {
+        rule_id: rule.id.clone(),
+        rule_name: rule.name.clone(),
+        status,
+        message,
+        severity: rule.severity.clone(),
+        weight: rule.weight(),
+        remediation: rule.remediation.clone(),
+    })
+}
+
+/// Evaluate a rule condition, recursing into AND/OR/NOT
+fn evaluate(g: &mut Guestfs, root: &str, rule_type: &RuleType) -> Result<ValidationStatus> {
+    let status = match rule_type {
         RuleType::PackageInstalled { package } => {
             check_package_installed(g, root, package)?
         }
@@ -206,26 +302,64 @@ fn validate_rule(
             // Port checking requires more complex parsing
             ValidationStatus::Skip
         }
-        RuleType::Custom { check: _ } => {
-            // Custom checks would be implemented here
-            ValidationStatus::Skip
+        RuleType::Custom { check } => {
+            check_custom(g, check)?
         }
+        RuleType::FileMatches { path, pattern } => {
+            check_file_matches(g, path, pattern)?
+        }
+        RuleType::FileMode { path, mode, owner, group } => {
+            check_file_mode(g, path, mode, owner.as_deref(), group.as_deref())?
+        }
+        RuleType::Sysctl { key, value } => {
+            check_sysctl(g, key, value)
+        }
+        RuleType::KernelModuleDisabled { module } => {
+            check_kernel_module_disabled(g, module)?
+        }
+        RuleType::MountOptions { mount_point, options } => {
+            check_mount_options(g, mount_point, options)?
+        }
+        RuleType::ConfigValue { paths, key, op, value, default } => {
+            check_config_value(g, paths, key, *op, value, default.as_deref())?
+        }
+        RuleType::And { rules } => {
+            let mut statuses = Vec::with_capacity(rules.len());
+            for rule in rules {
+                statuses.push(evaluate(g, root, rule)?);
+            }
+            combine(&statuses, ValidationStatus::Fail)
+        }
+        RuleType::Or { rules } => {
+            let mut statuses = Vec::with_capacity(rules.len());
+            for rule in rules {
+                statuses.push(evaluate(g, root, rule)?);
+            }
+            combine(&statuses, ValidationStatus::Pass)
+        }
+        RuleType::Not { rule } => match evaluate(g, root, rule)? {
+            ValidationStatus::Pass => ValidationStatus::Fail,
+            ValidationStatus::Fail => ValidationStatus::Pass,
+            other => other,
+        },
     };
+    Ok(status)
+}
 
-    let message = if status == ValidationStatus::Pass {
-        format!("{} - Check passed", rule.name)
-    } else {
-        format!("{} - Check failed", rule.name)
-    };
-
-    Ok(ValidationResult {
-        rule_id: rule.id.clone(),
-        rule_name: rule.name.clone(),
-        status,
-        message,
-        severity: rule.severity.clone(),
-        remediation: rule.remediation.clone(),
-    })
+/// Combine nested results: `decisive` (Fail for AND, Pass for OR) wins,
+/// otherwise any status that is neither Pass nor Fail makes the result
+/// undecided, and it is reported as the most serious of those.
+fn combine(statuses: &[ValidationStatus], decisive: ValidationStatus) -> ValidationStatus {
+    if statuses.contains(&decisive) {
+        return decisive;
+    }
+    [ValidationStatus::Error, ValidationStatus::Warning, ValidationStatus::Skip]
+        .into_iter()
+        .find(|status| statuses.contains(status))
+        .unwrap_or(match decisive {
+            ValidationStatus::Fail => ValidationStatus::Pass,
+            _ => ValidationStatus::Fail,
+        })
 }
 
 // Rule check implementations
@@ -282,20 +416,41 @@ fn check_file_permissions(g: &mut Guestfs, path: &str, expected_mode: &str) -> R
 }
 
 fn check_service_enabled(g: &mut Guestfs, service: &str) -> Result<ValidationStatus> {
-    // Check if systemd unit is enabled
-    let service_path = format!("/etc/systemd/system/multi-user.target.wants/{}.service", service);
-    let enabled = g.exists(&service_path)?;
-
+    let enabled = service_enabled(g, service)?;
     Ok(if enabled { ValidationStatus::Pass } else { ValidationStatus::Fail })
 }
 
 fn check_service_disabled(g: &mut Guestfs, service: &str) -> Result<ValidationStatus> {
-    let service_path = format!("/etc/systemd/system/multi-user.target.wants/{}.service", service);
-    let enabled = g.exists(&service_path)?;
-
+    let enabled = service_enabled(g, service)?;
     Ok(if enabled { ValidationStatus::Fail } else { ValidationStatus::Pass })
 }
 
+/// Whether a systemd unit (`.service` unless given) is wanted by any target
+/// and not masked
+fn service_enabled(g: &mut Guestfs, service: &str) -> Result<bool> {
+    let unit = if service.contains('.') {
+        service.to_string()
+    } else {
+        format!("{}.service", service)
+    };
+    let masked = format!("/etc/systemd/system/{}", unit);
+    if g.is_symlink(&masked).unwrap_or(false) && g.readlink(&masked)? == "/dev/null" {
+        return Ok(false);
+    }
+    if !g.is_dir("/etc/systemd/system")? {
+        return Ok(false);
+    }
+    for dir in g.ls("/etc/systemd/system")? {
+        if dir.ends_with(".wants") {
+            let path = format!("/etc/systemd/system/{}/{}", dir, unit);
+            if g.is_symlink(&path).unwrap_or(false) || g.exists(&path)? {
+                return Ok(true);
+            }
+        }
+    }
+    Ok(false)
+}
+
 fn check_user_exists(g: &mut Guestfs, username: &str) -> Result<ValidationStatus> {
     if !g.exists("/etc/passwd")? {
         return Ok(ValidationStatus::Error);
@@ -327,6 +482,206 @@ fn check_user_not_exists(g: &mut Guestfs, username: &str) -> Result<ValidationSt
 }
 
 
+fn check_custom(g: &mut Guestfs, check: &str) -> Result<ValidationStatus> {
+    let passwd = read_text(g, "/etc/passwd")?.unwrap_or_default();
+    let shadow = read_text(g, "/etc/shadow")?.unwrap_or_default();
+    let group = read_text(g, "/etc/group")?.unwrap_or_default();
+    if let Some(passed) = rules::account_check(check, &passwd, &shadow, &group) {
+        return Ok(if passed { ValidationStatus::Pass } else { ValidationStatus::Fail });
+    }
+
+    let dot_file = match check {
+        "home_dirs_exist" => None,
+        "no_rhosts_files" => Some(".rhosts"),
+        "no_netrc_files" => Some(".netrc"),
+        "no_forward_files" => Some(".forward"),
+        _ => return Ok(ValidationStatus::Skip),
+    };
+    let homes: Vec<String> = rego::parse_passwd(&passwd)
+        .into_iter()
+        .filter(|user| user.uid >= 1000 && user.uid != 65534)
+        .filter(|user| !user.shell.ends_with("/nologin") && !user.shell.ends_with("/false"))
+        .map(|user| user.home)
+        .collect();
+    for home in homes {
+        let failed = match dot_file {
+            None => !g.is_dir(&home)?,
+            Some(name) => g.exists(&format!("{}/{}", home.trim_end_matches('/'), name))?,
+        };
+        if failed {
+            return Ok(ValidationStatus::Fail);
+        }
+    }
+    Ok(ValidationStatus::Pass)
+}
+
+fn check_file_matches(g: &mut Guestfs, path: &str, pattern: &str) -> Result<ValidationStatus> {
+    let Ok(re) = regex::Regex::new(&format!("(?m){}", pattern)) else {
+        return Ok(ValidationStatus::Error);
+    };
+    for file in expand(g, path)? {
+        if let Some(text) = read_text(g, &file)? {
+            if re.is_match(&text) {
+                return Ok(ValidationStatus::Pass);
+            }
+        }
+    }
+    Ok(ValidationStatus::Fail)
+}
+
+fn check_file_mode(
+    g: &mut Guestfs,
+    path: &str,
+    mode: &str,
+    owner: Option<&str>,
+    group: Option<&str>,
+) -> Result<ValidationStatus> {
+    let Ok(max) = u32::from_str_radix(mode, 8) else {
+        return Ok(ValidationStatus::Error);
+    };
+    let files = expand(g, path)?;
+    if files.is_empty() {
+        return Ok(ValidationStatus::Skip);
+    }
+    let passwd = read_text(g, "/etc/passwd")?.unwrap_or_default();
+    let groups = read_text(g, "/etc/group")?.unwrap_or_default();
+    for file in files {
+        let stat = g.stat(&file)?;
+        let owned = |expected: Option<&str>, id: u32, names: &str| {
+            expected.is_none_or(|expected| {
+                let name = name_of(names, id);
+                expected
+                    .split('|')
+                    .any(|want| want == id.to_string() || Some(want) == name.as_deref())
+            })
+        };
+        if stat.mode & 0o7777 & !max != 0
+            || !owned(owner, stat.uid, &passwd)
+            || !owned(group, stat.gid, &groups)
+        {
+            return Ok(ValidationStatus::Fail);
+        }
+    }
+    Ok(ValidationStatus::Pass)
+}
+
+fn check_sysctl(g: &mut Guestfs, key: &str, value: &str) -> ValidationStatus {
+    let normalize = |v: &str| v.split_whitespace().collect::<Vec<_>>().join(" ");
+    match rego::sysctls(g).get(key) {
+        Some(actual) if normalize(actual) == normalize(value) => ValidationStatus::Pass,
+        _ => ValidationStatus::Fail,
+    }
+}
+
+fn check_kernel_module_disabled(g: &mut Guestfs, module: &str) -> Result<ValidationStatus> {
+    for dir in ["/etc/modprobe.d", "/run/modprobe.d", "/usr/lib/modprobe.d", "/lib/modprobe.d"] {
+        for file in expand(g, &format!("{}/*.conf", dir))? {
+            if let Some(text) = read_text(g, &file)? {
+                if rules::module_disabled(&text, module) {
+                    return Ok(ValidationStatus::Pass);
+                }
+            }
+        }
+    }
+    Ok(ValidationStatus::Fail)
+}
+
+fn check_mount_options(
+    g: &mut Guestfs,
+    mount_point: &str,
+    options: &[String],
+) -> Result<ValidationStatus> {
+    let fstab = read_text(g, "/etc/fstab")?.unwrap_or_default();
+    let mut actual = rules::fstab_options(&fstab, mount_point);
+    if actual.is_none() {
+        // A systemd mount unit, e.g. tmp.mount for /tmp
+        let unit = match mount_point.trim_matches('/') {
+            "" => "-".to_string(),
+            name => name.replace('/', "-"),
+        };
+        let unit = format!("/etc/systemd/system/{}.mount", unit);
+        if let Some(text) = read_text(g, &unit)? {
+            actual = Some(
+                rules::config_setting(&text, "Options")
+                    .map(|opts| opts.split(',').map(str::to_string).collect())
+                    .unwrap_or_default(),
+            );
+        }
+    }
+    Ok(match actual {
+        Some(actual) if options.iter().all(|opt| actual.contains(opt)) => ValidationStatus::Pass,
+        _ => ValidationStatus::Fail,
+    })
+}
+
+fn check_config_value(
+    g: &mut Guestfs,
+    paths: &[String],
+    key: &str,
+    op: CompareOp,
+    value: &str,
+    default: Option<&str>,
+) -> Result<ValidationStatus> {
+    let mut setting = None;
+    'paths: for path in paths {
+        for file in expand(g, path)? {
+            if let Some(text) = read_text(g, &file)? {
+                setting = rules::config_setting(&text, key);
+                if setting.is_some() {
+                    break 'paths;
+                }
+            }
+        }
+    }
+    let Some(actual) = setting.as_deref().or(default) else {
+        return Ok(ValidationStatus::Fail);
+    };
+    Ok(if rules::compare(actual, op, value) {
+        ValidationStatus::Pass
+    } else {
+        ValidationStatus::Fail
+    })
+}
+
+/// Guest paths matching `path`, whose last component may be a glob
+fn expand(g: &mut Guestfs, path: &str) -> Result<Vec<String>> {
+    let (dir, name) = path.rsplit_once('/').unwrap_or(("", path));
+    if !name.contains(['*', '?', '[']) {
+        return Ok(if g.exists(path)? { vec![path.to_string()] } else { Vec::new() });
+    }
+    let dir = if dir.is_empty() { "/" } else { dir };
+    let pattern = glob::Pattern::new(name)?;
+    if !g.is_dir(dir)? {
+        return Ok(Vec::new());
+    }
+    let mut names: Vec<String> = g.ls(dir)?
+        .into_iter()
+        .filter(|entry| pattern.matches(entry))
+        .collect();
+    names.sort();
+    Ok(names
+        .into_iter()
+        .map(|entry| format!("{}/{}", dir.trim_end_matches('/'), entry))
+        .collect())
+}
+
+fn read_text(g: &mut Guestfs, path: &str) -> Result<Option<String>> {
+    if !g.is_file(path)? {
+        return Ok(None);
+    }
+    let data = g.read_file(path)?;
+    Ok(Some(String::from_utf8_lossy(&data).into_owned()))
+}
+
+/// Name of the passwd or group entry with ID `id`
+fn name_of(entries: &str, id: u32) -> Option<String> {
+    entries.lines().find_map(|line| {
+        let fields: Vec<_> = line.split(':').collect();
+        (fields.len() >= 3 && fields[2] == id.to_string()).then(|| fields[0].to_string())
+    })
+}
+
+
 /// Format validation report as text
 pub fn format_report(report: &ValidationReport) -> String {
     let mut output = String::new();
@@ -334,6 +689,7 @@ pub fn format_report(report: &ValidationReport) -> String {
     output.push_str(&format!("🔍 Policy Validation Report\n"));
     output.push_str(&format!("==========================\n\n"));
     output.push_str(&format!("Image: {}\n", report.image_path));
+    output.push_str(&format!("OS root: {}\n", report.root));
     output.push_str(&format!("Policy: {}\n", report.policy_name));
     output.push_str(&format!("Time: {}\n\n", report.timestamp));
 
@@ -391,3 +747,50 @@ pub fn format_report(report: &ValidationReport) -> String {
 
     output
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn result(status: ValidationStatus, weight: f64) -> ValidationResult {
+        ValidationResult {
+            rule_id: String::new(),
+            rule_name: String::new(),
+            status,
+            message: String::new(),
+            severity: String::new(),
+            weight,
+            remediation: None,
+        }
+    }
+
+    #[test]
+    fn test_combine() {
+        use ValidationStatus::*;
+
+        assert_eq!(combine(&[Pass, Pass], Fail), Pass);
+        assert_eq!(combine(&[Pass, Fail, Error], Fail), Fail);
+        assert_eq!(combine(&[Pass, Skip], Fail), Skip);
+        assert_eq!(combine(&[], Fail), Pass);
+
+        assert_eq!(combine(&[Fail, Pass], Pass), Pass);
+        assert_eq!(combine(&[Fail, Skip, Error], Pass), Error);
+        assert_eq!(combine(&[Fail], Pass), Fail);
+        assert_eq!(combine(&[], Pass), Fail);
+    }
+
+    #[test]
+    fn test_weighted_compliance_score() {
+        let summary = ValidationSummary::new(&[
+            result(ValidationStatus::Pass, 1.0),
+            result(ValidationStatus::Fail, 3.0),
+            result(ValidationStatus::Skip, 10.0),
+        ]);
+        assert_eq!(summary.passed, 1);
+        assert_eq!(summary.skipped, 1);
+        assert_eq!(summary.compliance_score, 25.0);
+
+        let summary = ValidationSummary::new(&[result(ValidationStatus::Skip, 1.0)]);
+        assert_eq!(summary.compliance_score, 0.0);
+    }
+}
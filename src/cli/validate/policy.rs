@@ -1,10 +1,18 @@
 // SPDX-License-Identifier: LGPL-3.0-or-later
 //! Policy definitions and loading
+//!
+//! A policy may `extends` other policy files: their variables and rules come
+//! first, and a rule with the same id in the extending policy replaces the
+//! inherited one. `${name}` in any rule field is replaced with the merged
+//! `variables` once all files are loaded, so a base policy can leave values
+//! such as `${ssh_port}` to the policies built on it.
 
-use anyhow::Result;
+use anyhow::{bail, Context, Result};
 use serde::{Deserialize, Serialize};
+use serde_yaml::Value;
+use std::collections::BTreeMap;
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Security/compliance policy
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -12,6 +20,12 @@ pub struct Policy {
     pub name: String,
     pub version: String,
     pub description: String,
+    /// Policy files to inherit from, relative to this one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub extends: Vec<String>,
+    /// Values for `${name}` references in rules
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub variables: BTreeMap<String, Value>,
     pub rules: Vec<PolicyRule>,
 }
 
@@ -22,34 +36,184 @@ pub struct PolicyRule {
     pub name: String,
     pub description: String,
     pub severity: String,
+    /// Share of the compliance score; defaults to the severity's weight
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub weight: Option<f64>,
+    /// Benchmark profile level (CIS Level 1 or 2); rules without one
+    /// apply at every level
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level: Option<u8>,
     pub rule_type: RuleType,
     pub remediation: Option<String>,
 }
 
+impl PolicyRule {
+    pub fn weight(&self) -> f64 {
+        self.weight
+            .unwrap_or_else(|| severity_weight(&self.severity))
+    }
+}
+
+/// Default weight of a rule with severity `severity`
+pub fn severity_weight(severity: &str) -> f64 {
+    match super::rules::parse_severity(severity).as_str() {
+        "critical" => 10.0,
+        "high" => 5.0,
+        "medium" => 3.0,
+        _ => 1.0,
+    }
+}
+
 /// Types of validation rules
 #[derive(Debug, Clone, Serialize, Deserialize)]
 #[serde(tag = "type", rename_all = "snake_case")]
 pub enum RuleType {
-    PackageInstalled { package: String },
-    PackageForbidden { package: String },
-    FileExists { path: String },
-    FileNotExists { path: String },
-    FileContains { path: String, pattern: String },
-    FilePermissions { path: String, mode: String },
-    ServiceEnabled { service: String },
-    ServiceDisabled { service: String },
-    UserExists { username: String },
-    UserNotExists { username: String },
-    PortClosed { port: u16 },
-    Custom { check: String },
+    PackageInstalled {
+        package: String,
+    },
+    PackageForbidden {
+        package: String,
+    },
+    FileExists {
+        path: String,
+    },
+    FileNotExists {
+        path: String,
+    },
+    FileContains {
+        path: String,
+        pattern: String,
+    },
+    FilePermissions {
+        path: String,
+        mode: String,
+    },
+    ServiceEnabled {
+        service: String,
+    },
+    ServiceDisabled {
+        service: String,
+    },
+    UserExists {
+        username: String,
+    },
+    UserNotExists {
+        username: String,
+    },
+    PortClosed {
+        port: u16,
+    },
+    Custom {
+        check: String,
+    },
+    /// A regex (multi-line) matches a file; `path` may end in a glob
+    FileMatches {
+        path: String,
+        pattern: String,
+    },
+    /// Every file matching `path` grants no more than `mode`, and has the
+    /// owner and group (names or IDs, `|` for alternatives) if given
+    FileMode {
+        path: String,
+        mode: String,
+        owner: Option<String>,
+        group: Option<String>,
+    },
+    /// A sysctl setting is configured to `value`
+    Sysctl {
+        key: String,
+        value: String,
+    },
+    /// modprobe configuration keeps a kernel module from loading
+    KernelModuleDisabled {
+        module: String,
+    },
+    /// A mount point has its own fstab entry, with every option given
+    MountOptions {
+        mount_point: String,
+        #[serde(default)]
+        options: Vec<String>,
+    },
+    /// The first `key` setting in `paths` compares with `value`; `default`
+    /// is used when it is not set
+    ConfigValue {
+        paths: Vec<String>,
+        key: String,
+        #[serde(default)]
+        op: CompareOp,
+        value: String,
+        default: Option<String>,
+    },
+    /// Passes when every nested rule passes
+    And {
+        rules: Vec<RuleType>,
+    },
+    /// Passes when any nested rule passes
+    Or {
+        rules: Vec<RuleType>,
+    },
+    /// Passes when the nested rule fails
+    Not {
+        rule: Box<RuleType>,
+    },
+}
+
+/// How `config_value` compares a setting; numbers compare numerically,
+/// other values case-insensitively
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum CompareOp {
+    #[default]
+    Eq,
+    Ne,
+    Lt,
+    Le,
+    Gt,
+    Ge,
+    /// `value` is a regex the setting must match
+    Matches,
 }
 
 impl Policy {
-    /// Load policy from YAML file
+    /// Load policy from YAML file, with inherited policies and variables
     pub fn from_file<P: AsRef<Path>>(path: P) -> Result<Self> {
-        let content = fs::read_to_string(path)?;
-        let policy: Policy = serde_yaml::from_str(&content)?;
-        Ok(policy)
+        Self::resolve(PolicyFile::load(path.as_ref(), &mut Vec::new())?)
+    }
+
+    /// Load a policy from YAML text; it may not extend other policies
+    pub fn from_yaml(content: &str) -> Result<Self> {
+        let file: PolicyFile = serde_yaml::from_str(content)?;
+        if !file.extends.is_empty() {
+            bail!("Policy {} extends other policies", file.name);
+        }
+        Self::resolve(file)
+    }
+
+    /// Only the rules that apply at profile level `level`
+    pub fn at_level(mut self, level: u8) -> Self {
+        self.rules
+            .retain(|rule| rule.level.is_none_or(|rule_level| rule_level <= level));
+        self
+    }
+
+    fn resolve(file: PolicyFile) -> Result<Self> {
+        // Rules stay untyped until substituted: `port: ${ssh_port}` is not
+        // a valid port before that
+        let mut rules = Vec::with_capacity(file.rules.len());
+        for mut rule in file.rules {
+            let id = rule_id(&rule).unwrap_or("?").to_string();
+            substitute(&mut rule, &file.variables).with_context(|| format!("Rule {}", id))?;
+            rules.push(serde_yaml::from_value(rule).with_context(|| format!("Rule {}", id))?);
+        }
+
+        Ok(Self {
+            name: file.name,
+            version: file.version,
+            description: file.description,
+            extends: file.extends,
+            variables: file.variables,
+            rules,
+        })
     }
 
     /// Create example policy
@@ -58,12 +222,16 @@ impl Policy {
             name: "Example Security Policy".to_string(),
             version: "1.0.0".to_string(),
             description: "Example policy for demonstration".to_string(),
+            extends: Vec::new(),
+            variables: BTreeMap::new(),
             rules: vec![
                 PolicyRule {
                     id: "PKG-001".to_string(),
                     name: "OpenSSH Server Installed".to_string(),
                     description: "Ensure OpenSSH server is installed".to_string(),
                     severity: "medium".to_string(),
+                    weight: None,
+                    level: None,
                     rule_type: RuleType::PackageInstalled {
                         package: "openssh-server".to_string(),
                     },
@@ -74,6 +242,8 @@ impl Policy {
                     name: "Telnet Not Installed".to_string(),
                     description: "Ensure telnet is not installed".to_string(),
                     severity: "high".to_string(),
+                    weight: None,
+                    level: None,
                     rule_type: RuleType::PackageForbidden {
                         package: "telnet".to_string(),
                     },
@@ -84,6 +254,8 @@ impl Policy {
                     name: "Password File Exists".to_string(),
                     description: "Ensure /etc/passwd exists".to_string(),
                     severity: "critical".to_string(),
+                    weight: None,
+                    level: None,
                     rule_type: RuleType::FileExists {
                         path: "/etc/passwd".to_string(),
                     },
@@ -94,6 +266,8 @@ impl Policy {
                     name: "SSH Config Permissions".to_string(),
                     description: "Ensure /etc/ssh/sshd_config has correct permissions".to_string(),
                     severity: "high".to_string(),
+                    weight: None,
+                    level: None,
                     rule_type: RuleType::FilePermissions {
                         path: "/etc/ssh/sshd_config".to_string(),
                         mode: "600".to_string(),
@@ -105,6 +279,8 @@ impl Policy {
                     name: "SSH Service Enabled".to_string(),
                     description: "Ensure SSH service is enabled".to_string(),
                     severity: "medium".to_string(),
+                    weight: None,
+                    level: None,
                     rule_type: RuleType::ServiceEnabled {
                         service: "sshd".to_string(),
                     },
@@ -115,6 +291,8 @@ impl Policy {
                     name: "Root User Exists".to_string(),
                     description: "Ensure root user exists".to_string(),
                     severity: "critical".to_string(),
+                    weight: None,
+                    level: None,
                     rule_type: RuleType::UserExists {
                         username: "root".to_string(),
                     },
@@ -132,3 +310,252 @@ impl Policy {
         Ok(())
     }
 }
+
+/// A policy file as written, before variables are substituted
+#[derive(Deserialize)]
+struct PolicyFile {
+    name: String,
+    version: String,
+    description: String,
+    #[serde(default)]
+    extends: Vec<String>,
+    #[serde(default)]
+    variables: BTreeMap<String, Value>,
+    rules: Vec<Value>,
+}
+
+impl PolicyFile {
+    /// Load `path` merged with the policies it extends
+    fn load(path: &Path, chain: &mut Vec<PathBuf>) -> Result<Self> {
+        let canonical = fs::canonicalize(path)
+            .with_context(|| format!("Failed to read policy {}", path.display()))?;
+        if chain.contains(&canonical) {
+            bail!("Policy {} extends itself", path.display());
+        }
+
+        let content = fs::read_to_string(path)?;
+        let file: PolicyFile = serde_yaml::from_str(&content)
+            .with_context(|| format!("Invalid policy {}", path.display()))?;
+        if file.extends.is_empty() {
+            return Ok(file);
+        }
+
+        chain.push(canonical);
+        let dir = path.parent().unwrap_or(Path::new("."));
+        let mut variables = BTreeMap::new();
+        let mut rules = Vec::new();
+        for parent in &file.extends {
+            let parent = Self::load(&dir.join(parent), chain)?;
+            variables.extend(parent.variables);
+            merge_rules(&mut rules, parent.rules);
+        }
+        chain.pop();
+
+        variables.extend(file.variables);
+        merge_rules(&mut rules, file.rules);
+        Ok(Self {
+            variables,
+            rules,
+            ..file
+        })
+    }
+}
+
+fn rule_id(rule: &Value) -> Option<&str> {
+    rule.get("id").and_then(Value::as_str)
+}
+
+/// Append `rules`, replacing inherited rules with the same id in place
+fn merge_rules(merged: &mut Vec<Value>, rules: Vec<Value>) {
+    for rule in rules {
+        let existing =
+            rule_id(&rule).and_then(|id| merged.iter().position(|r| rule_id(r) == Some(id)));
+        match existing {
+            Some(i) => merged[i] = rule,
+            None => merged.push(rule),
+        }
+    }
+}
+
+/// Substitute variables in every string of `value`
+///
+/// A string that is exactly one `${name}` takes the variable's value as is,
+/// so numeric fields such as `port: ${ssh_port}` keep their type.
+fn substitute(value: &mut Value, variables: &BTreeMap<String, Value>) -> Result<()> {
+    match value {
+        Value::String(text) => {
+            if !text.contains("${") {
+                return Ok(());
+            }
+            if let Some(name) = text
+                .strip_prefix("${")
+                .and_then(|rest| rest.strip_suffix('}'))
+                .filter(|name| !name.contains(['$', '{', '}']))
+            {
+                *value = lookup(variables, name)?.clone();
+                return Ok(());
+            }
+
+            let mut result = String::new();
+            let mut rest = text.as_str();
+            while let Some(start) = rest.find("${") {
+                let Some(end) = rest[start..].find('}') else {
+                    bail!("Unterminated variable reference in '{}'", text);
+                };
+                result.push_str(&rest[..start]);
+                let name = &rest[start + 2..start + end];
+                match lookup(variables, name)? {
+                    Value::String(s) => result.push_str(s),
+                    other => result.push_str(serde_yaml::to_string(other)?.trim_end()),
+                }
+                rest = &rest[start + end + 1..];
+            }
+            result.push_str(rest);
+            *text = result;
+        }
+        Value::Sequence(items) => {
+            for item in items {
+                substitute(item, variables)?;
+            }
+        }
+        Value::Mapping(map) => {
+            for (_, item) in map.iter_mut() {
+                substitute(item, variables)?;
+            }
+        }
+        Value::Tagged(tagged) => substitute(&mut tagged.value, variables)?,
+        _ => {}
+    }
+    Ok(())
+}
+
+fn lookup<'a>(variables: &'a BTreeMap<String, Value>, name: &str) -> Result<&'a Value> {
+    variables
+        .get(name)
+        .with_context(|| format!("Undefined policy variable '{}'", name))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn write(dir: &Path, name: &str, content: &str) -> PathBuf {
+        let path = dir.join(name);
+        fs::write(&path, content).unwrap();
+        path
+    }
+
+    const BASE: &str = r#"
+name: Base
+version: "1"
+description: Base policy
+variables:
+  ssh_port: 22
+  sshd_config: /etc/ssh/sshd_config
+rules:
+  - id: SSH-1
+    name: SSH port
+    description: sshd listens on ${ssh_port}
+    severity: high
+    rule_type:
+      type: file_contains
+      path: ${sshd_config}
+      pattern: Port ${ssh_port}
+  - id: NET-1
+    name: SSH port closed
+    description: ""
+    severity: low
+    rule_type:
+      type: not
+      rule:
+        type: port_closed
+        port: ${ssh_port}
+"#;
+
+    #[test]
+    fn test_extends_and_variables() {
+        let dir = tempfile::tempdir().unwrap();
+        write(dir.path(), "base.yaml", BASE);
+        let child = write(
+            dir.path(),
+            "child.yaml",
+            r#"
+name: Child
+version: "2"
+description: Hardened
+extends: [base.yaml]
+variables:
+  ssh_port: 2222
+rules:
+  - id: NET-1
+    name: Telnet or rsh absent
+    description: ""
+    severity: critical
+    weight: 7
+    rule_type:
+      type: or
+      rules:
+        - type: package_forbidden
+          package: telnet
+        - type: and
+          rules:
+            - type: file_not_exists
+              path: /usr/bin/rsh
+            - type: file_not_exists
+              path: /usr/bin/rlogin
+    remediation: null
+"#,
+        );
+
+        let policy = Policy::from_file(&child).unwrap();
+        assert_eq!(policy.name, "Child");
+        assert_eq!(policy.rules.len(), 2);
+
+        let ssh = &policy.rules[0];
+        assert_eq!(ssh.description, "sshd listens on 2222");
+        assert!(matches!(
+            &ssh.rule_type,
+            RuleType::FileContains { path, pattern }
+                if path == "/etc/ssh/sshd_config" && pattern == "Port 2222"
+        ));
+        assert_eq!(ssh.weight(), 5.0);
+
+        let net = &policy.rules[1];
+        assert_eq!(net.name, "Telnet or rsh absent");
+        assert_eq!(net.weight(), 7.0);
+        assert!(matches!(&net.rule_type, RuleType::Or { rules } if rules.len() == 2));
+    }
+
+    #[test]
+    fn test_typed_variable() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = write(dir.path(), "base.yaml", BASE);
+        let policy = Policy::from_file(base).unwrap();
+        match &policy.rules[1].rule_type {
+            RuleType::Not { rule } => {
+                assert!(matches!(**rule, RuleType::PortClosed { port: 22 }))
+            }
+            other => panic!("unexpected rule {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_load_errors() {
+        let dir = tempfile::tempdir().unwrap();
+        let looped = write(
+            dir.path(),
+            "loop.yaml",
+            "name: L\nversion: '1'\ndescription: ''\nextends: [loop.yaml]\nrules: []\n",
+        );
+        let err = Policy::from_file(looped).unwrap_err();
+        assert!(err.to_string().contains("extends itself"));
+
+        let undefined = write(
+            dir.path(),
+            "undefined.yaml",
+            &BASE.replace("  ssh_port: 22\n", ""),
+        );
+        let err = Policy::from_file(undefined).unwrap_err();
+        assert!(format!("{:#}", err).contains("Undefined policy variable 'ssh_port'"));
+    }
+}
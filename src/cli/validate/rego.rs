@@ -0,0 +1,566 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Rego policies evaluated against image facts
+//!
+//! Inspection produces one JSON document per operating system (packages,
+//! enabled services, users, security-relevant files and sysctls), which is
+//! the `input` of the policies. Rules follow the conftest convention:
+//! messages in `deny`, `violation` (and `deny_*`, `violation_*`) are
+//! failures, messages in `warn` (and `warn_*`) are warnings. A message is a
+//! string, or an object with `msg` and optional `severity` and
+//! `remediation`. Evaluation needs the `rego` feature.
+
+use super::ValidationResult;
+use anyhow::{Context, Result};
+use guestkit::Guestfs;
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// Files whose metadata, and content unless marked false, are facts
+const FACT_FILES: &[(&str, bool)] = &[
+    ("/etc/passwd", true),
+    ("/etc/shadow", false),
+    ("/etc/group", true),
+    ("/etc/gshadow", false),
+    ("/etc/login.defs", true),
+    ("/etc/ssh/sshd_config", true),
+    ("/etc/sudoers", true),
+    ("/etc/fstab", true),
+    ("/etc/hosts", true),
+    ("/etc/crontab", true),
+    ("/etc/securetty", true),
+    ("/etc/selinux/config", true),
+    ("/etc/default/grub", true),
+    ("/boot/grub/grub.cfg", false),
+    ("/boot/grub2/grub.cfg", false),
+    ("/etc/security/pwquality.conf", true),
+    ("/etc/security/faillock.conf", true),
+    ("/etc/pam.d/common-auth", true),
+    ("/etc/pam.d/common-password", true),
+    ("/etc/pam.d/system-auth", true),
+    ("/etc/pam.d/password-auth", true),
+    ("/etc/audit/auditd.conf", true),
+    ("/etc/issue", true),
+    ("/etc/issue.net", true),
+    ("/etc/motd", true),
+];
+
+/// Directories whose files are facts, with content
+const FACT_DIRS: &[&str] = &[
+    "/etc/ssh/sshd_config.d",
+    "/etc/sudoers.d",
+    "/etc/modprobe.d",
+    "/etc/sysctl.d",
+    "/etc/cron.d",
+    "/etc/audit/rules.d",
+];
+
+/// Where sysctl settings are read from, directories first; later files win
+const SYSCTL_DIRS: &[&str] = &["/usr/lib/sysctl.d", "/run/sysctl.d", "/etc/sysctl.d"];
+const SYSCTL_FILES: &[&str] = &["/etc/sysctl.conf"];
+
+/// File content larger than this is left out
+const MAX_CONTENT: i64 = 1024 * 1024;
+
+/// The `input` document of a Rego policy
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ImageFacts {
+    pub os: OsFacts,
+    pub packages: Vec<PackageFact>,
+    pub services: ServiceFacts,
+    pub users: Vec<UserFact>,
+    pub files: BTreeMap<String, FileFact>,
+    pub sysctls: BTreeMap<String, String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct OsFacts {
+    pub root: String,
+    #[serde(rename = "type")]
+    pub os_type: String,
+    pub distro: String,
+    pub major_version: i32,
+    pub minor_version: i32,
+    pub hostname: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct PackageFact {
+    pub name: String,
+    pub version: String,
+    pub release: String,
+}
+
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct ServiceFacts {
+    pub enabled: Vec<String>,
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct UserFact {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub home: String,
+    pub shell: String,
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileFact {
+    /// Permission bits in octal, as in `file_permissions` rules
+    pub mode: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: i64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub content: Option<String>,
+}
+
+/// Inspect the mounted operating system at `root`
+pub fn collect_facts(g: &mut Guestfs, root: &str) -> Result<ImageFacts> {
+    let os = OsFacts {
+        root: root.to_string(),
+        os_type: g.inspect_get_type(root).unwrap_or_default(),
+        distro: g.inspect_get_distro(root).unwrap_or_default(),
+        major_version: g.inspect_get_major_version(root).unwrap_or_default(),
+        minor_version: g.inspect_get_minor_version(root).unwrap_or_default(),
+        hostname: g.inspect_get_hostname(root).unwrap_or_default(),
+    };
+
+    let packages = g
+        .inspect_list_applications2(root)
+        .unwrap_or_default()
+        .into_iter()
+        .map(|(name, version, release)| PackageFact {
+            name,
+            version,
+            release,
+        })
+        .collect();
+
+    let services = ServiceFacts {
+        enabled: g.list_enabled_services().unwrap_or_default(),
+    };
+
+    let mut files = BTreeMap::new();
+    for (path, with_content) in FACT_FILES {
+        if let Some(fact) = file_fact(g, path, *with_content) {
+            files.insert(path.to_string(), fact);
+        }
+    }
+    for dir in FACT_DIRS {
+        for path in dir_files(g, dir) {
+            if let Some(fact) = file_fact(g, &path, true) {
+                files.insert(path, fact);
+            }
+        }
+    }
+
+    let users = files
+        .get("/etc/passwd")
+        .and_then(|f| f.content.as_deref())
+        .map(parse_passwd)
+        .unwrap_or_default();
+
+    let sysctls = sysctls(g);
+
+    Ok(ImageFacts {
+        os,
+        packages,
+        services,
+        users,
+        files,
+        sysctls,
+    })
+}
+
+/// Effective sysctl settings of the guest
+pub fn sysctls(g: &mut Guestfs) -> BTreeMap<String, String> {
+    let mut sysctls = BTreeMap::new();
+    let mut sysctl_files = Vec::new();
+    for dir in SYSCTL_DIRS {
+        sysctl_files.extend(
+            dir_files(g, dir)
+                .into_iter()
+                .filter(|f| f.ends_with(".conf")),
+        );
+    }
+    sysctl_files.extend(SYSCTL_FILES.iter().map(|f| f.to_string()));
+    for path in sysctl_files {
+        if let Ok(data) = g.read_file(&path) {
+            parse_sysctl(&String::from_utf8_lossy(&data), &mut sysctls);
+        }
+    }
+    sysctls
+}
+
+fn file_fact(g: &mut Guestfs, path: &str, with_content: bool) -> Option<FileFact> {
+    if !g.is_file(path).unwrap_or(false) {
+        return None;
+    }
+    let stat = g.stat(path).ok()?;
+    let content = if with_content && stat.size <= MAX_CONTENT {
+        g.read_file(path)
+            .ok()
+            .map(|data| String::from_utf8_lossy(&data).into_owned())
+    } else {
+        None
+    };
+    Some(FileFact {
+        mode: format!("{:o}", stat.mode & 0o7777),
+        uid: stat.uid,
+        gid: stat.gid,
+        size: stat.size,
+        content,
+    })
+}
+
+/// Files directly in `dir`, sorted
+fn dir_files(g: &mut Guestfs, dir: &str) -> Vec<String> {
+    if !g.is_dir(dir).unwrap_or(false) {
+        return Vec::new();
+    }
+    let mut names = g.ls(dir).unwrap_or_default();
+    names.sort();
+    names
+        .into_iter()
+        .map(|name| format!("{}/{}", dir, name))
+        .collect()
+}
+
+/// Accounts of an /etc/passwd file
+pub fn parse_passwd(text: &str) -> Vec<UserFact> {
+    text.lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            if fields.len() < 7 || line.starts_with('#') {
+                return None;
+            }
+            Some(UserFact {
+                name: fields[0].to_string(),
+                uid: fields[2].parse().ok()?,
+                gid: fields[3].parse().ok()?,
+                home: fields[5].to_string(),
+                shell: fields[6].to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Add the `key = value` settings of a sysctl.conf file to `sysctls`
+///
+/// Keys are normalized to dots (`net/ipv4/ip_forward` is
+/// `net.ipv4.ip_forward`) and a leading `-` (ignore errors) is dropped.
+pub fn parse_sysctl(text: &str, sysctls: &mut BTreeMap<String, String>) {
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#') || line.starts_with(';') {
+            continue;
+        }
+        if let Some((key, value)) = line.split_once('=') {
+            let key = key.trim().trim_start_matches('-').replace('/', ".");
+            sysctls.insert(key, value.trim().to_string());
+        }
+    }
+}
+
+/// The `.rego` files at `path`: the file itself, or those in a directory
+#[cfg_attr(not(feature = "rego"), allow(dead_code))]
+pub fn policy_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        anyhow::ensure!(path.exists(), "Rego policy not found: {}", path.display());
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files = Vec::new();
+    for entry in
+        std::fs::read_dir(path).with_context(|| format!("Failed to read {}", path.display()))?
+    {
+        let file = entry?.path();
+        let is_test = file
+            .file_stem()
+            .and_then(|s| s.to_str())
+            .is_some_and(|s| s.ends_with("_test"));
+        if file.extension().and_then(|e| e.to_str()) == Some("rego") && !is_test {
+            files.push(file);
+        }
+    }
+    files.sort();
+    anyhow::ensure!(!files.is_empty(), "No .rego files in {}", path.display());
+    Ok(files)
+}
+
+/// Module and result parsing, only needed by the `rego` engine
+#[cfg(any(feature = "rego", test))]
+mod results {
+    use super::super::policy::severity_weight;
+    use super::super::rules::parse_severity;
+    use super::super::{ValidationResult, ValidationStatus};
+
+    /// The `package` a Rego module declares
+    pub(super) fn package_name(source: &str) -> Option<String> {
+        source.lines().find_map(|line| {
+            let rest = line.trim().strip_prefix("package")?;
+            if !rest.starts_with(char::is_whitespace) {
+                return None;
+            }
+            let name = rest.split('#').next()?.trim();
+            (!name.is_empty()).then(|| name.to_string())
+        })
+    }
+
+    /// Results of the deny/violation/warn rules in a package's document
+    pub(super) fn package_results(
+        package: &str,
+        document: &serde_json::Value,
+    ) -> Vec<ValidationResult> {
+        let Some(rules) = document.as_object() else {
+            return Vec::new();
+        };
+
+        let mut results = Vec::new();
+        for (rule, value) in rules {
+            let status = if is_rule(rule, "deny") || is_rule(rule, "violation") {
+                ValidationStatus::Fail
+            } else if is_rule(rule, "warn") {
+                ValidationStatus::Warning
+            } else {
+                continue;
+            };
+
+            let rule_id = format!("{}.{}", package, rule);
+            let messages: Vec<&serde_json::Value> = match value {
+                serde_json::Value::Array(items) => items.iter().collect(),
+                serde_json::Value::Null => Vec::new(),
+                other => vec![other],
+            };
+            if messages.is_empty() {
+                results.push(result(
+                    rule_id.clone(),
+                    rule_id,
+                    ValidationStatus::Pass,
+                    "high",
+                    None,
+                ));
+                continue;
+            }
+            for message in messages {
+                let field = |name: &str| message.get(name).and_then(|v| v.as_str());
+                let text = match message {
+                    serde_json::Value::String(text) => text.clone(),
+                    _ => field("msg")
+                        .map(str::to_string)
+                        .unwrap_or_else(|| message.to_string()),
+                };
+                results.push(result(
+                    rule_id.clone(),
+                    text,
+                    status.clone(),
+                    field("severity").unwrap_or("high"),
+                    field("remediation").map(str::to_string),
+                ));
+            }
+        }
+        results
+    }
+
+    /// `name` or `name_*`
+    fn is_rule(rule: &str, name: &str) -> bool {
+        rule.strip_prefix(name)
+            .is_some_and(|rest| rest.is_empty() || rest.starts_with('_'))
+    }
+
+    fn result(
+        rule_id: String,
+        rule_name: String,
+        status: ValidationStatus,
+        severity: &str,
+        remediation: Option<String>,
+    ) -> ValidationResult {
+        let severity = parse_severity(severity);
+        let message = if status == ValidationStatus::Pass {
+            format!("{} - Check passed", rule_name)
+        } else {
+            rule_name.clone()
+        };
+        ValidationResult {
+            rule_id,
+            rule_name,
+            status,
+            message,
+            weight: severity_weight(&severity),
+            severity,
+            remediation,
+        }
+    }
+}
+
+#[cfg(any(feature = "rego", test))]
+use results::{package_name, package_results};
+
+#[cfg(feature = "rego")]
+pub use engine::RegoPolicy;
+
+#[cfg(not(feature = "rego"))]
+pub use disabled::RegoPolicy;
+
+#[cfg(feature = "rego")]
+mod engine {
+    use super::*;
+    use std::collections::BTreeSet;
+
+    /// Loaded Rego modules
+    pub struct RegoPolicy {
+        engine: regorus::Engine,
+        packages: BTreeSet<String>,
+        name: String,
+    }
+
+    impl RegoPolicy {
+        /// Load the policy file or directory at `path`
+        pub fn load(path: &Path) -> Result<Self> {
+            let mut engine = regorus::Engine::new();
+            let mut packages = BTreeSet::new();
+            for file in policy_files(path)? {
+                let source = std::fs::read_to_string(&file)
+                    .with_context(|| format!("Failed to read {}", file.display()))?;
+                packages.extend(package_name(&source));
+                engine
+                    .add_policy(file.display().to_string(), source)
+                    .with_context(|| format!("Invalid Rego in {}", file.display()))?;
+            }
+            Ok(Self {
+                engine,
+                packages,
+                name: path.display().to_string(),
+            })
+        }
+
+        /// Policy name for reports
+        pub fn name(&self) -> &str {
+            &self.name
+        }
+
+        /// Evaluate every package with `facts` as input
+        pub fn evaluate(&self, facts: &ImageFacts) -> Result<Vec<ValidationResult>> {
+            let mut engine = self.engine.clone();
+            engine.set_input(regorus::Value::from_json_str(&serde_json::to_string(
+                facts,
+            )?)?);
+
+            let mut results = Vec::new();
+            for package in &self.packages {
+                let query = format!("data.{}", package);
+                let output = engine
+                    .eval_query(query.clone(), false)
+                    .with_context(|| format!("Failed to evaluate {}", query))?;
+                let output = serde_json::to_value(&output)?;
+                let document = output
+                    .pointer("/result/0/expressions/0/value")
+                    .cloned()
+                    .unwrap_or_default();
+                results.extend(package_results(package, &document));
+            }
+            Ok(results)
+        }
+    }
+}
+
+#[cfg(not(feature = "rego"))]
+mod disabled {
+    use super::*;
+
+    /// Loaded Rego modules; none without the `rego` feature
+    pub enum RegoPolicy {}
+
+    impl RegoPolicy {
+        pub fn load(_path: &Path) -> Result<Self> {
+            anyhow::bail!("Rego policies not enabled. Rebuild with --features rego.");
+        }
+
+        pub fn name(&self) -> &str {
+            match *self {}
+        }
+
+        pub fn evaluate(&self, _facts: &ImageFacts) -> Result<Vec<ValidationResult>> {
+            match *self {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::cli::validate::ValidationStatus;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_passwd() {
+        let users = parse_passwd(
+            "root:x:0:0:root:/root:/bin/bash\n# comment\nbroken\n\
+             alice:x:1000:1000:Alice:/home/alice:/bin/zsh\n",
+        );
+        assert_eq!(users.len(), 2);
+        assert_eq!(users[1].name, "alice");
+        assert_eq!(users[1].uid, 1000);
+        assert_eq!(users[1].shell, "/bin/zsh");
+    }
+
+    #[test]
+    fn test_parse_sysctl() {
+        let mut sysctls = BTreeMap::new();
+        parse_sysctl(
+            "net.ipv4.ip_forward = 1\nkernel.randomize_va_space=2\n",
+            &mut sysctls,
+        );
+        parse_sysctl(
+            "# override\n; also a comment\n-net/ipv4/ip_forward = 0\n",
+            &mut sysctls,
+        );
+        assert_eq!(sysctls["net.ipv4.ip_forward"], "0");
+        assert_eq!(sysctls["kernel.randomize_va_space"], "2");
+        assert_eq!(sysctls.len(), 2);
+    }
+
+    #[test]
+    fn test_package_name() {
+        assert_eq!(
+            package_name("# policy\npackage main.ssh # comment\n\ndeny[msg] { true }").as_deref(),
+            Some("main.ssh")
+        );
+        assert_eq!(package_name("packages := 1"), None);
+    }
+
+    #[test]
+    fn test_package_results() {
+        let document = json!({
+            "deny": ["telnet is installed"],
+            "violation_ssh": [{
+                "msg": "root login allowed",
+                "severity": "critical",
+                "remediation": "Set PermitRootLogin no"
+            }],
+            "warn": [],
+            "allow": true,
+            "denylist": ["not a rule"],
+        });
+        let results = package_results("main", &document);
+        assert_eq!(results.len(), 3);
+
+        assert_eq!(results[0].rule_id, "main.deny");
+        assert_eq!(results[0].rule_name, "telnet is installed");
+        assert_eq!(results[0].status, ValidationStatus::Fail);
+        assert_eq!(results[0].severity, "high");
+
+        assert_eq!(results[1].rule_id, "main.violation_ssh");
+        assert_eq!(results[1].severity, "critical");
+        assert_eq!(results[1].weight, 10.0);
+        assert_eq!(
+            results[1].remediation.as_deref(),
+            Some("Set PermitRootLogin no")
+        );
+
+        assert_eq!(results[2].rule_id, "main.warn");
+        assert_eq!(results[2].status, ValidationStatus::Pass);
+    }
+}
@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: LGPL-3.0-or-later
 //! Rule evaluation helpers
 
+use super::policy::CompareOp;
 use anyhow::Result;
 
 /// Evaluate a custom rule expression
@@ -12,7 +13,6 @@ pub fn evaluate_custom_rule(_expression: &str) -> Result<bool> {
 }
 
 /// Parse severity level
-#[allow(dead_code)]
 pub fn parse_severity(s: &str) -> String {
     match s.to_lowercase().as_str() {
         "critical" | "crit" => "critical".to_string(),
@@ -22,3 +22,262 @@ pub fn parse_severity(s: &str) -> String {
         _ => "medium".to_string(),
     }
 }
+
+/// Evaluate a named account check against passwd, shadow and group
+/// content; `None` if `check` is not an account check
+///
+/// These are the `custom` checks of the bundled benchmarks.
+pub fn account_check(check: &str, passwd: &str, shadow: &str, group: &str) -> Option<bool> {
+    let passwd = entries(passwd);
+    let shadow = entries(shadow);
+    let group = entries(group);
+    let shadow_gid = group.iter().find(|g| g[0] == "shadow").map(|g| g[2]);
+
+    let passed = match check {
+        "uid0_only_root" => passwd.iter().all(|u| u[2] != "0" || u[0] == "root"),
+        "root_gid_0" => passwd.iter().any(|u| u[0] == "root" && u[3] == "0"),
+        "group_root_only_gid_0" => group.iter().all(|g| g[2] != "0" || g[0] == "root"),
+        "shadowed_passwords" => passwd.iter().all(|u| u[1] == "x"),
+        "no_empty_passwords" => shadow.iter().all(|s| !s[1].is_empty()),
+        "no_duplicate_uids" => unique(passwd.iter().map(|u| u[2])),
+        "no_duplicate_gids" => unique(group.iter().map(|g| g[2])),
+        "no_duplicate_user_names" => unique(passwd.iter().map(|u| u[0])),
+        "no_duplicate_group_names" => unique(group.iter().map(|g| g[0])),
+        "passwd_groups_exist" => passwd.iter().all(|u| group.iter().any(|g| g[2] == u[3])),
+        "shadow_group_empty" => {
+            group
+                .iter()
+                .all(|g| g[0] != "shadow" || g.get(3).is_none_or(|m| m.is_empty()))
+                && passwd.iter().all(|u| Some(u[3]) != shadow_gid)
+        }
+        "system_accounts_nologin" => passwd.iter().all(|u| {
+            let uid: u32 = u[2].parse().unwrap_or(u32::MAX);
+            uid >= 1000
+                || ["root", "sync", "shutdown", "halt"].contains(&u[0])
+                || u[6].ends_with("/nologin")
+                || u[6].ends_with("/false")
+        }),
+        _ => return None,
+    };
+    Some(passed)
+}
+
+/// The value of the first `key value` or `key=value` setting in a
+/// configuration file; keys are matched case-insensitively, section headers
+/// are skipped, and an sshd `Match` block ends the global settings
+pub fn config_setting(text: &str, key: &str) -> Option<String> {
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty()
+            || line.starts_with('#')
+            || line.starts_with(';')
+            || line.starts_with('[')
+        {
+            continue;
+        }
+        let split = line
+            .find(|c: char| c == '=' || c.is_whitespace())
+            .unwrap_or(line.len());
+        let (name, rest) = line.split_at(split);
+        if name.eq_ignore_ascii_case("match") {
+            break;
+        }
+        if name.eq_ignore_ascii_case(key) {
+            let value = rest.trim_start().trim_start_matches('=').trim();
+            return Some(value.trim_matches('"').to_string());
+        }
+    }
+    None
+}
+
+/// Compare a configured value with an expected one
+pub fn compare(actual: &str, op: CompareOp, expected: &str) -> bool {
+    if op == CompareOp::Matches {
+        return regex::Regex::new(expected).is_ok_and(|re| re.is_match(actual));
+    }
+    let ordering = match (actual.parse::<f64>(), expected.parse::<f64>()) {
+        (Ok(a), Ok(e)) => a.partial_cmp(&e),
+        _ => Some(actual.to_lowercase().cmp(&expected.to_lowercase())),
+    };
+    let Some(ordering) = ordering else {
+        return false;
+    };
+    match op {
+        CompareOp::Eq => ordering.is_eq(),
+        CompareOp::Ne => ordering.is_ne(),
+        CompareOp::Lt => ordering.is_lt(),
+        CompareOp::Le => ordering.is_le(),
+        CompareOp::Gt => ordering.is_gt(),
+        CompareOp::Ge => ordering.is_ge(),
+        CompareOp::Matches => unreachable!(),
+    }
+}
+
+/// Mount options of the fstab entry for `mount_point`
+pub fn fstab_options(fstab: &str, mount_point: &str) -> Option<Vec<String>> {
+    fstab
+        .lines()
+        .map(|line| line.split_whitespace().collect::<Vec<_>>())
+        .find(|fields| fields.len() >= 2 && !fields[0].starts_with('#') && fields[1] == mount_point)
+        .map(|fields| {
+            fields
+                .get(3)
+                .map(|opts| opts.split(',').map(str::to_string).collect())
+                .unwrap_or_default()
+        })
+}
+
+/// Whether a modprobe.d file makes loading `module` run `true` or `false`
+pub fn module_disabled(text: &str, module: &str) -> bool {
+    let normalize = |name: &str| name.replace('-', "_");
+    let module = normalize(module);
+    text.lines().any(|line| {
+        let fields: Vec<_> = line.split_whitespace().collect();
+        fields.len() >= 3
+            && fields[0] == "install"
+            && normalize(fields[1]) == module
+            && matches!(fields[2].rsplit('/').next(), Some("true" | "false"))
+    })
+}
+
+/// Colon-separated records of an /etc/passwd-style file
+fn entries(text: &str) -> Vec<Vec<&str>> {
+    text.lines()
+        .filter(|line| !line.trim().is_empty() && !line.starts_with('#'))
+        .map(|line| line.split(':').collect::<Vec<_>>())
+        .filter(|fields| fields.len() >= 3)
+        .map(|mut fields| {
+            fields.resize(7, "");
+            fields
+        })
+        .collect()
+}
+
+fn unique<'a>(values: impl Iterator<Item = &'a str>) -> bool {
+    let mut seen = std::collections::HashSet::new();
+    values.into_iter().all(|value| seen.insert(value))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PASSWD: &str = "root:x:0:0:root:/root:/bin/bash\n\
+        daemon:x:1:1:daemon:/usr/sbin:/usr/sbin/nologin\n\
+        alice:x:1000:1000::/home/alice:/bin/bash\n";
+    const SHADOW: &str = "root:$6$salt$hash:19000:0:99999:7:::\n\
+        daemon:*:19000:0:99999:7:::\n\
+        alice:$y$j9T$x:19000:1:365:7:30::\n";
+    const GROUP: &str = "root:x:0:\ndaemon:x:1:\nshadow:x:42:\nalice:x:1000:\n";
+
+    #[test]
+    fn test_account_check() {
+        for check in [
+            "uid0_only_root",
+            "root_gid_0",
+            "group_root_only_gid_0",
+            "shadowed_passwords",
+            "no_empty_passwords",
+            "no_duplicate_uids",
+            "no_duplicate_gids",
+            "no_duplicate_user_names",
+            "no_duplicate_group_names",
+            "passwd_groups_exist",
+            "shadow_group_empty",
+            "system_accounts_nologin",
+        ] {
+            assert_eq!(
+                account_check(check, PASSWD, SHADOW, GROUP),
+                Some(true),
+                "{}",
+                check
+            );
+        }
+        assert_eq!(account_check("unknown", PASSWD, SHADOW, GROUP), None);
+
+        let passwd = format!(
+            "{}toor:x:0:0::/root:/bin/sh\nftp:x:14:50::/srv:/bin/bash\n",
+            PASSWD
+        );
+        assert_eq!(
+            account_check("uid0_only_root", &passwd, SHADOW, GROUP),
+            Some(false)
+        );
+        assert_eq!(
+            account_check("no_duplicate_uids", &passwd, SHADOW, GROUP),
+            Some(false)
+        );
+        assert_eq!(
+            account_check("passwd_groups_exist", &passwd, SHADOW, GROUP),
+            Some(false)
+        );
+        assert_eq!(
+            account_check("system_accounts_nologin", &passwd, SHADOW, GROUP),
+            Some(false)
+        );
+
+        let shadow = "root::19000:0:99999:7:::\n";
+        assert_eq!(
+            account_check("no_empty_passwords", PASSWD, shadow, GROUP),
+            Some(false)
+        );
+
+        let group = "root:x:0:\nshadow:x:42:alice\n";
+        assert_eq!(
+            account_check("shadow_group_empty", PASSWD, SHADOW, group),
+            Some(false)
+        );
+    }
+
+    #[test]
+    fn test_config_setting() {
+        let sshd = "# comment\nPermitRootLogin no\nMaxAuthTries=4\n\
+            ciphers \"aes256-ctr\"\nMatch User backup\n  PermitRootLogin yes\n\
+            X11Forwarding yes\n";
+        assert_eq!(
+            config_setting(sshd, "permitrootlogin").as_deref(),
+            Some("no")
+        );
+        assert_eq!(config_setting(sshd, "MaxAuthTries").as_deref(), Some("4"));
+        assert_eq!(
+            config_setting(sshd, "Ciphers").as_deref(),
+            Some("aes256-ctr")
+        );
+        assert_eq!(config_setting(sshd, "X11Forwarding"), None);
+
+        let journald = "[Journal]\nStorage = persistent\n";
+        assert_eq!(
+            config_setting(journald, "Storage").as_deref(),
+            Some("persistent")
+        );
+    }
+
+    #[test]
+    fn test_compare() {
+        assert!(compare("4", CompareOp::Le, "4"));
+        assert!(compare("300", CompareOp::Le, "900"));
+        assert!(!compare("1000", CompareOp::Le, "900"));
+        assert!(compare("NO", CompareOp::Eq, "no"));
+        assert!(compare("yes", CompareOp::Ne, "no"));
+        assert!(compare("VERBOSE", CompareOp::Matches, "^(INFO|VERBOSE)$"));
+    }
+
+    #[test]
+    fn test_fstab_options() {
+        let fstab = "# /tmp was here\nUUID=1 / ext4 defaults 0 1\n\
+            tmpfs /tmp tmpfs defaults,nodev,nosuid,noexec 0 0\n";
+        assert_eq!(
+            fstab_options(fstab, "/tmp").unwrap(),
+            vec!["defaults", "nodev", "nosuid", "noexec"]
+        );
+        assert_eq!(fstab_options(fstab, "/var"), None);
+    }
+
+    #[test]
+    fn test_module_disabled() {
+        let conf = "install cramfs /bin/false\nblacklist cramfs\ninstall usb-storage /bin/true\n";
+        assert!(module_disabled(conf, "cramfs"));
+        assert!(module_disabled(conf, "usb_storage"));
+        assert!(!module_disabled(conf, "squashfs"));
+    }
+}
@@ -0,0 +1,215 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! vulndb command - fetch and query the offline vulnerability database
+
+use super::{default_sources, update, Source, VulnDb};
+use crate::cli::plain;
+use anyhow::Result;
+use clap::{Args, Subcommand};
+use colored::*;
+
+#[derive(Debug, Args)]
+pub struct VulndbCommand {
+    #[command(subcommand)]
+    pub action: VulndbAction,
+}
+
+#[derive(Debug, Subcommand)]
+pub enum VulndbAction {
+    /// Download the feeds and rebuild the database
+    Update {
+        /// OSV feed: a zip of records, a directory or a JSON file, local or
+        /// a URL (repeatable; replaces the default feeds)
+        #[arg(long = "osv", value_name = "LOCATION")]
+        osv: Vec<String>,
+
+        /// OVAL definitions of one release, e.g. `Red Hat:9=rhel-9.oval.xml.bz2`
+        /// (repeatable; replaces the default feeds)
+        #[arg(long = "oval", value_name = "ECOSYSTEM=LOCATION", value_parser = parse_oval)]
+        oval: Vec<Source>,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Show the feeds and the age of the database
+    Status {
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+
+    /// Look up the advisories for one package version
+    Lookup {
+        /// Ecosystem, e.g. `Debian:12`, `Ubuntu:22.04`, `Alpine:v3.19`
+        ecosystem: String,
+
+        /// Package name (source package for Debian, Ubuntu and Alpine)
+        package: String,
+
+        /// Installed version
+        version: String,
+
+        /// Output format (text, json)
+        #[arg(short, long, default_value = "text")]
+        format: String,
+    },
+}
+
+fn parse_oval(value: &str) -> Result<Source, String> {
+    match value.split_once('=') {
+        Some((ecosystem, location)) if !ecosystem.is_empty() && !location.is_empty() => {
+            Ok(Source::Oval {
+                ecosystem: ecosystem.to_string(),
+                location: location.to_string(),
+            })
+        }
+        _ => Err(format!("expected ECOSYSTEM=LOCATION, got `{}`", value)),
+    }
+}
+
+impl VulndbCommand {
+    pub fn execute(&self) -> Result<()> {
+        match &self.action {
+            VulndbAction::Update { osv, oval, format } => {
+                let mut sources: Vec<Source> = osv
+                    .iter()
+                    .map(|location| Source::Osv {
+                        location: location.clone(),
+                    })
+                    .collect();
+                sources.extend(oval.iter().cloned());
+                if sources.is_empty() {
+                    sources = default_sources();
+                }
+
+                if format != "json" {
+                    println!(
+                        "{} Fetching {} vulnerability feeds...",
+                        plain::text("→").cyan(),
+                        sources.len()
+                    );
+                }
+                let db = update(&sources)?;
+                print_status(&db, format)
+            }
+            VulndbAction::Status { format } => print_status(&VulnDb::open()?, format),
+            VulndbAction::Lookup {
+                ecosystem,
+                package,
+                version,
+                format,
+            } => lookup(&VulnDb::open()?, ecosystem, package, version, format),
+        }
+    }
+}
+
+fn print_status(db: &VulnDb, format: &str) -> Result<()> {
+    if format == "json" {
+        let status = serde_json::json!({
+            "path": VulnDb::path(),
+            "updated": db.updated,
+            "advisories": db.len(),
+            "feeds": db.feeds,
+            "ecosystems": db.ecosystems(),
+        });
+        println!("{}", serde_json::to_string_pretty(&status)?);
+        return Ok(());
+    }
+
+    println!("Vulnerability database: {}", VulnDb::path().display());
+    let age = db
+        .age()
+        .map(|age| format!(" ({} days ago)", age.num_days()))
+        .unwrap_or_default();
+    println!("Updated: {}{}", db.updated, age);
+    println!("Advisories: {}", db.len());
+    println!();
+    for feed in &db.feeds {
+        match &feed.error {
+            None => println!(
+                "  {} {} ({} advisories)",
+                plain::text("✓").green(),
+                feed.name,
+                feed.advisories
+            ),
+            Some(error) => println!("  {} {}: {}", plain::text("✗").red(), feed.name, error),
+        }
+    }
+    println!();
+    println!("Affected packages by ecosystem:");
+    for (ecosystem, packages) in db.ecosystems() {
+        println!("  {:24} {}", ecosystem, packages);
+    }
+    Ok(())
+}
+
+fn lookup(db: &VulnDb, ecosystem: &str, package: &str, version: &str, format: &str) -> Result<()> {
+    let findings = db.lookup(ecosystem, package, version);
+
+    if format == "json" {
+        let findings: Vec<_> = findings
+            .iter()
+            .map(|finding| {
+                serde_json::json!({
+                    "advisory": finding.advisory,
+                    "fixed_version": finding.fixed,
+                })
+            })
+            .collect();
+        println!("{}", serde_json::to_string_pretty(&findings)?);
+        return Ok(());
+    }
+
+    if !db.covers(ecosystem) {
+        println!(
+            "{} The database has no advisories for {}",
+            plain::text("⚠").yellow(),
+            ecosystem
+        );
+        return Ok(());
+    }
+    if findings.is_empty() {
+        println!(
+            "{} {} {}: no known vulnerabilities",
+            plain::text("✓").green(),
+            package,
+            version
+        );
+        return Ok(());
+    }
+    for finding in findings {
+        let advisory = finding.advisory;
+        println!(
+            "{} [{}] {}",
+            advisory.id.bold(),
+            advisory.severity.as_deref().unwrap_or("unknown"),
+            advisory.summary
+        );
+        if !advisory.cves.is_empty() && advisory.cves != [advisory.id.clone()] {
+            println!("   CVEs: {}", advisory.cves.join(", "));
+        }
+        match finding.fixed {
+            Some(fixed) => println!("   Fixed in: {}", fixed),
+            None => println!("   Fixed in: {}", "no fix yet".yellow()),
+        }
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_oval() {
+        assert_eq!(
+            parse_oval("Red Hat:9=https://example.org/rhel-9.oval.xml.bz2?a=b").unwrap(),
+            Source::Oval {
+                ecosystem: "Red Hat:9".to_string(),
+                location: "https://example.org/rhel-9.oval.xml.bz2?a=b".to_string(),
+            }
+        );
+        assert!(parse_oval("rhel-9.oval.xml").is_err());
+    }
+}
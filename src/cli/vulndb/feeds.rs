@@ -0,0 +1,302 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Fetching and reading the feeds
+//!
+//! Downloads are kept under the cache directory and fetched again only when
+//! the server has a newer copy.
+
+use super::{cache_dir, osv, oval, FeedStatus, VulnDb};
+use anyhow::{bail, Context, Result};
+use chrono::Utc;
+use std::fs::File;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+const OSV_BUCKET: &str = "https://osv-vulnerabilities.storage.googleapis.com";
+/// Distros whose advisories OSV carries
+const OSV_ECOSYSTEMS: &[&str] = &["Debian", "Ubuntu", "Alpine", "AlmaLinux", "Rocky Linux"];
+/// Red Hat releases, whose advisories come as OVAL only
+const RHEL_RELEASES: &[u32] = &[8, 9];
+
+/// A feed, at a URL or in a local file or directory
+#[derive(Debug, Clone, PartialEq)]
+pub enum Source {
+    /// OSV records: a zip of them, a directory or one JSON file
+    Osv { location: String },
+    /// OVAL definitions of one release, optionally compressed
+    Oval { ecosystem: String, location: String },
+}
+
+impl Source {
+    pub fn name(&self) -> String {
+        match self {
+            Self::Osv { location } => format!("osv {}", location),
+            Self::Oval { ecosystem, .. } => format!("oval {}", ecosystem),
+        }
+    }
+
+    pub fn location(&self) -> &str {
+        match self {
+            Self::Osv { location } | Self::Oval { location, .. } => location,
+        }
+    }
+}
+
+/// The feeds `vulndb update` reads unless given others
+pub fn default_sources() -> Vec<Source> {
+    let osv = OSV_ECOSYSTEMS.iter().map(|ecosystem| Source::Osv {
+        location: format!("{}/{}/all.zip", OSV_BUCKET, ecosystem.replace(' ', "%20")),
+    });
+    let oval = RHEL_RELEASES.iter().map(|release| Source::Oval {
+        ecosystem: format!("Red Hat:{}", release),
+        location: format!(
+            "https://security.access.redhat.com/data/oval/v2/RHEL{0}/rhel-{0}.oval.xml.bz2",
+            release
+        ),
+    });
+    osv.chain(oval).collect()
+}
+
+/// Read every source into a new database and save it
+///
+/// A source that cannot be read is skipped with a warning; the update fails
+/// only if none could be.
+pub fn update(sources: &[Source]) -> Result<VulnDb> {
+    let mut db = VulnDb {
+        updated: Utc::now().to_rfc3339(),
+        ..Default::default()
+    };
+
+    for source in sources {
+        let before = db.len();
+        let error = match read(source, &mut db) {
+            Ok(()) => None,
+            Err(e) => {
+                log::warn!("Skipping {}: {:#}", source.name(), e);
+                Some(format!("{:#}", e))
+            }
+        };
+        db.feeds.push(FeedStatus {
+            name: source.name(),
+            location: source.location().to_string(),
+            advisories: db.len() - before,
+            error,
+        });
+    }
+
+    if db.feeds.iter().all(|feed| feed.error.is_some()) {
+        bail!("None of the vulnerability feeds could be read");
+    }
+    // Saving an empty index would report every package as unaffected
+    if db.is_empty() {
+        bail!("The vulnerability feeds contained no advisories");
+    }
+    db.save()?;
+    Ok(db)
+}
+
+fn read(source: &Source, db: &mut VulnDb) -> Result<()> {
+    let path = fetch(source.location())?;
+    match source {
+        Source::Osv { .. } => read_osv(&path, db),
+        Source::Oval { ecosystem, .. } => {
+            let xml = decompress(&path)?;
+            for record in oval::parse(&String::from_utf8_lossy(&xml), ecosystem)? {
+                db.add(record);
+            }
+            Ok(())
+        }
+    }
+}
+
+/// OSV records from a zip, a directory of JSON files or one JSON file
+fn read_osv(path: &Path, db: &mut VulnDb) -> Result<()> {
+    let unpacked;
+    let dir = if path.is_dir() {
+        path
+    } else if is_zip(path)? {
+        unpacked = tempfile::tempdir()?;
+        unzip(path, unpacked.path())?;
+        unpacked.path()
+    } else {
+        return read_osv_file(path, db);
+    };
+
+    let pattern = format!("{}/**/*.json", dir.display());
+    for file in glob::glob(&pattern)?.filter_map(|entry| entry.ok()) {
+        if let Err(e) = read_osv_file(&file, db) {
+            log::debug!("Skipping {}: {:#}", file.display(), e);
+        }
+    }
+    Ok(())
+}
+
+fn is_zip(path: &Path) -> Result<bool> {
+    let mut magic = [0; 4];
+    let read = File::open(path)?.read(&mut magic)?;
+    Ok(magic[..read] == *b"PK\x03\x04")
+}
+
+/// One record, or an array of them
+fn read_osv_file(path: &Path, db: &mut VulnDb) -> Result<()> {
+    let content = std::fs::read(path)?;
+    let value: serde_json::Value = serde_json::from_slice(&content)
+        .with_context(|| format!("Invalid JSON in {}", path.display()))?;
+    let records = match value.as_array() {
+        Some(records) => records.iter().collect(),
+        None => vec![&value],
+    };
+    for record in records.into_iter().filter_map(osv::parse) {
+        db.add(record);
+    }
+    Ok(())
+}
+
+fn is_url(location: &str) -> bool {
+    location.starts_with("http://") || location.starts_with("https://")
+}
+
+/// The local copy of a feed, downloading URLs into the cache
+fn fetch(location: &str) -> Result<PathBuf> {
+    if !is_url(location) {
+        let path = PathBuf::from(location);
+        if !path.exists() {
+            bail!("{}: not found", location);
+        }
+        return Ok(path);
+    }
+
+    let dir = cache_dir().join("feeds");
+    std::fs::create_dir_all(&dir)?;
+    let dest = dir.join(feed_file_name(location));
+    let partial = dest.with_extension("part");
+    std::fs::remove_file(&partial).ok();
+
+    let mut curl = Command::new("curl");
+    curl.args(["-fsSL", "--retry", "2", "-R", "-o"])
+        .arg(&partial);
+    // Only download what changed since the last update
+    if dest.is_file() {
+        curl.arg("-z").arg(&dest);
+    }
+    let output = curl.arg(location).output().context("failed to run curl")?;
+    if !output.status.success() {
+        std::fs::remove_file(&partial).ok();
+        bail!("{}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+
+    // Nothing is written when the cached copy is current
+    match std::fs::metadata(&partial) {
+        Ok(meta) if meta.len() > 0 => std::fs::rename(&partial, &dest)?,
+        _ => {
+            std::fs::remove_file(&partial).ok();
+        }
+    }
+    if !dest.is_file() {
+        bail!("{}: empty download", location);
+    }
+    Ok(dest)
+}
+
+/// A file name for the cached copy of `url`
+fn feed_file_name(url: &str) -> String {
+    let url = url.split_once("://").map_or(url, |(_, rest)| rest);
+    url.chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect()
+}
+
+fn unzip(zip: &Path, dest: &Path) -> Result<()> {
+    let output = Command::new("unzip")
+        .arg("-qo")
+        .arg(zip)
+        .arg("-d")
+        .arg(dest)
+        .output()
+        .context("failed to run unzip")?;
+    if !output.status.success() {
+        bail!("unzip: {}", String::from_utf8_lossy(&output.stderr).trim());
+    }
+    Ok(())
+}
+
+/// Decompress gzip/bzip2/xz/zstd by magic bytes with the host tools
+fn decompress(path: &Path) -> Result<Vec<u8>> {
+    let bytes = std::fs::read(path)?;
+
+    let tool = if bytes.starts_with(&[0x1f, 0x8b]) {
+        "gzip"
+    } else if bytes.starts_with(b"BZh") {
+        "bzip2"
+    } else if bytes.starts_with(&[0xfd, b'7', b'z', b'X', b'Z', 0x00]) {
+        "xz"
+    } else if bytes.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        "zstd"
+    } else {
+        return Ok(bytes);
+    };
+
+    let output = Command::new(tool)
+        .arg("-dc")
+        .arg(path)
+        .output()
+        .with_context(|| format!("failed to run {}", tool))?;
+    if !output.status.success() {
+        bail!(
+            "{}: {}",
+            tool,
+            String::from_utf8_lossy(&output.stderr).trim()
+        );
+    }
+    Ok(output.stdout)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_default_sources() {
+        let sources = default_sources();
+        assert!(sources.contains(&Source::Osv {
+            location: "https://osv-vulnerabilities.storage.googleapis.com/Rocky%20Linux/all.zip"
+                .to_string()
+        }));
+        assert!(sources
+            .iter()
+            .any(|s| matches!(s, Source::Oval { ecosystem, .. } if ecosystem == "Red Hat:9")));
+    }
+
+    #[test]
+    fn test_feed_file_name() {
+        assert_eq!(
+            feed_file_name("https://osv-vulnerabilities.storage.googleapis.com/Debian/all.zip"),
+            "osv-vulnerabilities.storage.googleapis.com_Debian_all.zip"
+        );
+    }
+
+    #[test]
+    fn test_read_osv_dir() {
+        let dir = tempfile::tempdir().unwrap();
+        std::fs::write(
+            dir.path().join("ALPINE-CVE-2024-0727.json"),
+            r#"{"id":"ALPINE-CVE-2024-0727","upstream":["CVE-2024-0727"],
+                "affected":[{"package":{"ecosystem":"Alpine:v3.19","name":"openssl"},
+                "ranges":[{"type":"ECOSYSTEM","events":[{"introduced":"0"},{"fixed":"3.1.4-r5"}]}]}]}"#,
+        )
+        .unwrap();
+        std::fs::write(dir.path().join("broken.json"), "{").unwrap();
+
+        let mut db = VulnDb::default();
+        read_osv(dir.path(), &mut db).unwrap();
+        assert_eq!(db.len(), 1);
+        assert_eq!(db.lookup("Alpine:v3.19", "openssl", "3.1.4-r4").len(), 1);
+        assert!(db.lookup("Alpine:v3.19", "openssl", "3.1.4-r5").is_empty());
+    }
+}
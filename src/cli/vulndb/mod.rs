@@ -0,0 +1,376 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Offline vulnerability database
+//!
+//! `guestctl vulndb update` downloads the distros' OSV feeds, and OVAL
+//! definitions for those OSV does not cover, and keeps an index of the
+//! affected package versions in the user cache directory. Lookups only read
+//! that index, so checking a guest needs no network.
+
+pub mod command;
+pub mod cvss;
+mod feeds;
+mod osv;
+mod oval;
+pub mod version;
+pub mod vex;
+
+pub use command::VulndbCommand;
+pub use feeds::{default_sources, update, Source};
+
+use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
+use guestkit::Guestfs;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::path::PathBuf;
+use version::Scheme;
+
+/// Age after which lookups warn that the database is stale
+pub const MAX_AGE_DAYS: i64 = 7;
+
+/// An advisory as a feed publishes it
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Advisory {
+    pub id: String,
+    /// CVE IDs it covers
+    pub cves: Vec<String>,
+    /// critical, high, medium, low or none
+    pub severity: Option<String>,
+    pub score: Option<f64>,
+    pub summary: String,
+}
+
+/// Versions from `introduced` up to `fixed`, or up to and including
+/// `last_affected`; unbounded where unset
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Range {
+    pub introduced: Option<String>,
+    pub fixed: Option<String>,
+    pub last_affected: Option<String>,
+}
+
+/// A package of one ecosystem an advisory affects
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AffectedPackage {
+    pub ecosystem: String,
+    pub name: String,
+    pub scheme: Scheme,
+    pub ranges: Vec<Range>,
+    /// Affected versions listed one by one
+    pub versions: Vec<String>,
+}
+
+/// An advisory with the packages it affects, as parsed from a feed
+#[derive(Debug, Clone, Default)]
+pub struct Record {
+    pub advisory: Advisory,
+    pub packages: Vec<AffectedPackage>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct Affected {
+    advisory: usize,
+    scheme: Scheme,
+    ranges: Vec<Range>,
+    versions: Vec<String>,
+}
+
+impl Affected {
+    /// The fix for `version`, `Some(None)` if there is none yet, or `None`
+    /// if `version` is not affected
+    fn check(&self, version: &str) -> Option<Option<&str>> {
+        let first_fix = || self.ranges.iter().find_map(|r| r.fixed.as_deref());
+        if self.versions.iter().any(|v| v == version) {
+            return Some(first_fix());
+        }
+        let cmp = |other: &str| self.scheme.compare(version, other);
+        self.ranges
+            .iter()
+            .find(|range| {
+                let after_start = match range.introduced.as_deref() {
+                    None | Some("0") => true,
+                    Some(introduced) => cmp(introduced).is_ge(),
+                };
+                let before_end = match (&range.fixed, &range.last_affected) {
+                    (Some(fixed), _) => cmp(fixed).is_lt(),
+                    (None, Some(last)) => cmp(last).is_le(),
+                    (None, None) => true,
+                };
+                after_start && before_end
+            })
+            .map(|range| range.fixed.as_deref())
+    }
+}
+
+/// A feed the database was built from
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedStatus {
+    pub name: String,
+    pub location: String,
+    pub advisories: usize,
+    /// Why the feed could not be read
+    pub error: Option<String>,
+}
+
+/// An advisory affecting an installed version
+#[derive(Debug)]
+pub struct Finding<'a> {
+    pub advisory: &'a Advisory,
+    pub fixed: Option<&'a str>,
+}
+
+/// The index of affected package versions
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct VulnDb {
+    /// When the feeds were fetched, RFC 3339
+    pub updated: String,
+    pub feeds: Vec<FeedStatus>,
+    advisories: Vec<Advisory>,
+    packages: HashMap<(String, String), Vec<Affected>>,
+}
+
+impl VulnDb {
+    /// Where the index is kept
+    pub fn path() -> PathBuf {
+        cache_dir().join("vulndb.bin")
+    }
+
+    /// Load the index built by `guestctl vulndb update`
+    pub fn open() -> Result<Self> {
+        let path = Self::path();
+        let bytes = std::fs::read(&path).with_context(|| {
+            format!(
+                "No vulnerability database at {}; run `guestctl vulndb update` first",
+                path.display()
+            )
+        })?;
+        let db: Self = bincode::deserialize(&bytes)
+            .with_context(|| format!("Corrupt vulnerability database {}", path.display()))?;
+
+        if let Some(age) = db.age().filter(|age| age.num_days() >= MAX_AGE_DAYS) {
+            log::warn!(
+                "The vulnerability database is {} days old; run `guestctl vulndb update`",
+                age.num_days()
+            );
+        }
+        Ok(db)
+    }
+
+    /// Write the index, replacing the previous one only once complete
+    pub fn save(&self) -> Result<()> {
+        let path = Self::path();
+        std::fs::create_dir_all(cache_dir())?;
+        let partial = path.with_extension("part");
+        std::fs::write(&partial, bincode::serialize(self)?)?;
+        std::fs::rename(&partial, &path)?;
+        Ok(())
+    }
+
+    pub fn add(&mut self, record: Record) {
+        let advisory = self.advisories.len();
+        self.advisories.push(record.advisory);
+        for package in record.packages {
+            self.packages
+                .entry((package.ecosystem, package.name))
+                .or_default()
+                .push(Affected {
+                    advisory,
+                    scheme: package.scheme,
+                    ranges: package.ranges,
+                    versions: package.versions,
+                });
+        }
+    }
+
+    /// Number of advisories
+    pub fn len(&self) -> usize {
+        self.advisories.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.advisories.is_empty()
+    }
+
+    /// Time since the feeds were fetched
+    pub fn age(&self) -> Option<chrono::Duration> {
+        let updated = DateTime::parse_from_rfc3339(&self.updated).ok()?;
+        Some(Utc::now().signed_duration_since(updated))
+    }
+
+    /// Number of affected packages in each ecosystem
+    pub fn ecosystems(&self) -> BTreeMap<&str, usize> {
+        let mut counts = BTreeMap::new();
+        for (ecosystem, _) in self.packages.keys() {
+            *counts.entry(ecosystem.as_str()).or_default() += 1;
+        }
+        counts
+    }
+
+    /// Whether any advisory covers `ecosystem`
+    pub fn covers(&self, ecosystem: &str) -> bool {
+        self.packages.keys().any(|(known, _)| known == ecosystem)
+    }
+
+    /// Advisories affecting `version` of `package`
+    pub fn lookup(&self, ecosystem: &str, package: &str, version: &str) -> Vec<Finding<'_>> {
+        if version.is_empty() {
+            return Vec::new();
+        }
+        let key = (ecosystem.to_string(), package.to_string());
+        self.packages
+            .get(&key)
+            .into_iter()
+            .flatten()
+            .filter_map(|affected| {
+                let fixed = affected.check(version)?;
+                Some(Finding {
+                    advisory: &self.advisories[affected.advisory],
+                    fixed,
+                })
+            })
+            .collect()
+    }
+}
+
+/// Directory of the index and the downloaded feeds
+pub fn cache_dir() -> PathBuf {
+    dirs::cache_dir()
+        .unwrap_or_else(std::env::temp_dir)
+        .join("guestctl")
+        .join("vulndb")
+}
+
+/// The OSV ecosystem of a distro release, as `inspect` names them
+pub fn ecosystem(distro: &str, major: i32, minor: i32) -> Option<String> {
+    Some(match distro {
+        "debian" => format!("Debian:{}", major),
+        "ubuntu" => format!("Ubuntu:{}.{:02}", major, minor),
+        "alpine" => format!("Alpine:v{}.{}", major, minor),
+        "alma" | "almalinux" => format!("AlmaLinux:{}", major),
+        "rocky" => format!("Rocky Linux:{}", major),
+        "rhel" => format!("Red Hat:{}", major),
+        "ol" => format!("Oracle Linux:{}", major),
+        _ => return None,
+    })
+}
+
+/// The ecosystem of the operating system at `root`
+pub fn os_ecosystem(g: &mut Guestfs, root: &str) -> Option<String> {
+    let distro = g.inspect_get_distro(root).ok()?;
+    let major = g.inspect_get_major_version(root).ok()?;
+    let minor = g.inspect_get_minor_version(root).unwrap_or(0);
+    ecosystem(&distro, major, minor)
+}
+
+/// Normalize the severity names of the feeds
+pub fn severity_name(text: &str) -> Option<&'static str> {
+    Some(match text.trim().to_lowercase().as_str() {
+        "critical" => "critical",
+        "high" | "important" => "high",
+        "medium" | "moderate" => "medium",
+        "low" | "negligible" | "unimportant" => "low",
+        _ => return None,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(id: &str, ecosystem: &str, name: &str, ranges: Vec<Range>) -> Record {
+        Record {
+            advisory: Advisory {
+                id: id.to_string(),
+                cves: vec![id.to_string()],
+                ..Default::default()
+            },
+            packages: vec![AffectedPackage {
+                ecosystem: ecosystem.to_string(),
+                name: name.to_string(),
+                scheme: Scheme::of(ecosystem),
+                ranges,
+                versions: Vec::new(),
+            }],
+        }
+    }
+
+    fn range(introduced: Option<&str>, fixed: Option<&str>) -> Range {
+        Range {
+            introduced: introduced.map(str::to_string),
+            fixed: fixed.map(str::to_string),
+            last_affected: None,
+        }
+    }
+
+    #[test]
+    fn test_lookup() {
+        let mut db = VulnDb::default();
+        db.add(record(
+            "CVE-2024-0727",
+            "Debian:12",
+            "openssl",
+            vec![range(Some("0"), Some("3.0.13-1~deb12u1"))],
+        ));
+        db.add(record(
+            "CVE-2024-2511",
+            "Debian:12",
+            "openssl",
+            vec![range(Some("3.0.0"), None)],
+        ));
+        db.add(record(
+            "CVE-2023-0001",
+            "Debian:11",
+            "openssl",
+            vec![range(None, Some("9.9"))],
+        ));
+
+        let found = db.lookup("Debian:12", "openssl", "3.0.11-1~deb12u2");
+        assert_eq!(found.len(), 2);
+        assert_eq!(found[0].advisory.id, "CVE-2024-0727");
+        assert_eq!(found[0].fixed, Some("3.0.13-1~deb12u1"));
+        assert_eq!(found[1].fixed, None);
+
+        let found = db.lookup("Debian:12", "openssl", "3.0.13-1~deb12u1");
+        assert_eq!(found.len(), 1);
+        assert_eq!(found[0].advisory.id, "CVE-2024-2511");
+
+        assert!(db.lookup("Debian:12", "openssl", "").is_empty());
+        assert!(db.lookup("Debian:12", "curl", "7.88.1-10").is_empty());
+        assert!(db.covers("Debian:11"));
+        assert_eq!(db.ecosystems()["Debian:12"], 1);
+    }
+
+    #[test]
+    fn test_last_affected_and_versions() {
+        let affected = Affected {
+            advisory: 0,
+            scheme: Scheme::Apk,
+            ranges: vec![Range {
+                introduced: Some("1.2.0-r0".to_string()),
+                fixed: None,
+                last_affected: Some("1.2.4-r2".to_string()),
+            }],
+            versions: vec!["1.1.0-r0".to_string()],
+        };
+        assert_eq!(affected.check("1.2.4-r2"), Some(None));
+        assert_eq!(affected.check("1.2.4-r3"), None);
+        assert_eq!(affected.check("1.1.9-r0"), None);
+        assert_eq!(affected.check("1.1.0-r0"), Some(None));
+    }
+
+    #[test]
+    fn test_ecosystem() {
+        assert_eq!(ecosystem("debian", 12, 5).as_deref(), Some("Debian:12"));
+        assert_eq!(ecosystem("ubuntu", 22, 4).as_deref(), Some("Ubuntu:22.04"));
+        assert_eq!(ecosystem("alpine", 3, 19).as_deref(), Some("Alpine:v3.19"));
+        assert_eq!(ecosystem("rocky", 9, 3).as_deref(), Some("Rocky Linux:9"));
+        assert_eq!(ecosystem("windows", 10, 0), None);
+    }
+
+    #[test]
+    fn test_severity_name() {
+        assert_eq!(severity_name("Important"), Some("high"));
+        assert_eq!(severity_name(" moderate"), Some("medium"));
+        assert_eq!(severity_name("not yet assigned"), None);
+    }
+}
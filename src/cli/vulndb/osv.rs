@@ -0,0 +1,240 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! OSV records, as <https://ossf.github.io/osv-schema/> describes them
+
+use super::version::Scheme;
+use super::{cvss, severity_name, Advisory, AffectedPackage, Range, Record};
+use serde_json::Value;
+
+/// An OSV record, unless withdrawn or affecting no versions
+pub(super) fn parse(record: &Value) -> Option<Record> {
+    if record["withdrawn"].is_string() {
+        return None;
+    }
+    let id = record["id"].as_str()?;
+
+    let mut cves = Vec::new();
+    let aliases = [&record["aliases"], &record["upstream"]]
+        .into_iter()
+        .filter_map(Value::as_array)
+        .flatten()
+        .filter_map(Value::as_str);
+    for cve in std::iter::once(id).chain(aliases) {
+        if cve.starts_with("CVE-") && !cves.iter().any(|known| known == cve) {
+            cves.push(cve.to_string());
+        }
+    }
+
+    let summary = record["summary"]
+        .as_str()
+        .filter(|summary| !summary.is_empty())
+        .or_else(|| record["details"].as_str()?.lines().next())
+        .unwrap_or_default()
+        .trim()
+        .to_string();
+
+    let affected = record["affected"].as_array().cloned().unwrap_or_default();
+    let (severity, score) = severity(record, &affected);
+    let packages: Vec<AffectedPackage> = affected.iter().filter_map(affected_package).collect();
+    if packages.is_empty() {
+        return None;
+    }
+
+    Some(Record {
+        advisory: Advisory {
+            id: id.to_string(),
+            cves,
+            severity,
+            score,
+            summary,
+        },
+        packages,
+    })
+}
+
+/// Severity and score, from a CVSS v3 vector where there is one, else from
+/// the distro's own rating
+fn severity(record: &Value, affected: &[Value]) -> (Option<String>, Option<f64>) {
+    let severities = std::iter::once(&record["severity"])
+        .chain(affected.iter().map(|a| &a["severity"]))
+        .filter_map(Value::as_array)
+        .flatten();
+
+    let mut score = None;
+    let mut rating = None;
+    for severity in severities {
+        let text = severity["score"].as_str().unwrap_or_default();
+        match severity["type"].as_str() {
+            Some("CVSS_V3") => score = score.or_else(|| cvss::base_score(text)),
+            _ => rating = rating.or_else(|| severity_name(text)),
+        }
+    }
+
+    // Debian rates urgency, AlmaLinux and Rocky Linux name a severity
+    let ratings = std::iter::once(&record["database_specific"]["severity"])
+        .chain(affected.iter().flat_map(|a| {
+            [
+                &a["ecosystem_specific"]["urgency"],
+                &a["database_specific"]["severity"],
+            ]
+        }))
+        .filter_map(Value::as_str);
+    for text in ratings {
+        rating = rating.or_else(|| severity_name(text));
+    }
+
+    let severity = score.map(cvss::rating).or(rating).map(str::to_string);
+    (severity, score)
+}
+
+fn affected_package(affected: &Value) -> Option<AffectedPackage> {
+    let package = &affected["package"];
+    let ecosystem = package["ecosystem"].as_str()?;
+    // Ubuntu's LTS releases are `Ubuntu:22.04:LTS`
+    let ecosystem = ecosystem.strip_suffix(":LTS").unwrap_or(ecosystem);
+
+    let mut ranges = Vec::new();
+    for range in affected["ranges"].as_array().into_iter().flatten() {
+        if !matches!(range["type"].as_str(), Some("ECOSYSTEM" | "SEMVER")) {
+            continue;
+        }
+        ranges.extend(parse_events(range["events"].as_array()?));
+    }
+    let versions: Vec<String> = affected["versions"]
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|v| v.as_str().map(str::to_string))
+        .collect();
+    if ranges.is_empty() && versions.is_empty() {
+        return None;
+    }
+
+    Some(AffectedPackage {
+        ecosystem: ecosystem.to_string(),
+        name: package["name"].as_str()?.to_string(),
+        scheme: Scheme::of(ecosystem),
+        ranges,
+        versions,
+    })
+}
+
+/// Pair each `introduced` event with the `fixed` or `last_affected` after it
+fn parse_events(events: &[Value]) -> Vec<Range> {
+    let mut ranges = Vec::new();
+    let mut open: Option<Range> = None;
+    for event in events {
+        let text = |key: &str| event[key].as_str().map(str::to_string);
+        if let Some(introduced) = text("introduced") {
+            ranges.extend(open.take());
+            open = Some(Range {
+                introduced: Some(introduced),
+                ..Default::default()
+            });
+        } else if let Some(fixed) = text("fixed") {
+            let mut range = open.take().unwrap_or_default();
+            range.fixed = Some(fixed);
+            ranges.push(range);
+        } else if let Some(last) = text("last_affected") {
+            let mut range = open.take().unwrap_or_default();
+            range.last_affected = Some(last);
+            ranges.push(range);
+        }
+    }
+    ranges.extend(open);
+    ranges
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_parse_debian() {
+        let record = json!({
+            "id": "DEBIAN-CVE-2024-0727",
+            "upstream": ["CVE-2024-0727"],
+            "details": "Processing a maliciously formatted PKCS12 file may lead OpenSSL to crash\nleading to a potential Denial of Service attack",
+            "affected": [{
+                "package": { "ecosystem": "Debian:12", "name": "openssl" },
+                "ranges": [{
+                    "type": "ECOSYSTEM",
+                    "events": [{ "introduced": "0" }, { "fixed": "3.0.13-1~deb12u1" }]
+                }],
+                "ecosystem_specific": { "urgency": "low" }
+            }]
+        });
+        let parsed = parse(&record).unwrap();
+        assert_eq!(parsed.advisory.cves, ["CVE-2024-0727"]);
+        assert_eq!(parsed.advisory.severity.as_deref(), Some("low"));
+        assert!(parsed.advisory.summary.starts_with("Processing"));
+        assert_eq!(parsed.packages[0].scheme, Scheme::Dpkg);
+        assert_eq!(
+            parsed.packages[0].ranges,
+            [Range {
+                introduced: Some("0".to_string()),
+                fixed: Some("3.0.13-1~deb12u1".to_string()),
+                last_affected: None,
+            }]
+        );
+    }
+
+    #[test]
+    fn test_parse_ubuntu() {
+        let record = json!({
+            "id": "UBUNTU-CVE-2023-44487",
+            "aliases": ["CVE-2023-44487", "GHSA-qppj-fm5r-hxr3"],
+            "summary": "HTTP/2 rapid reset",
+            "severity": [
+                { "type": "CVSS_V3", "score": "CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:N/I:N/A:H" },
+                { "type": "Ubuntu", "score": "high" }
+            ],
+            "affected": [{
+                "package": { "ecosystem": "Ubuntu:22.04:LTS", "name": "nginx" },
+                "ranges": [
+                    { "type": "ECOSYSTEM", "events": [{ "introduced": "0" }, { "fixed": "1.18.0-6ubuntu14.5" }] },
+                    { "type": "GIT", "repo": "https://example.org/nginx", "events": [{ "introduced": "0" }] }
+                ],
+                "versions": ["1.18.0-6ubuntu14", "1.18.0-6ubuntu14.4"]
+            }]
+        });
+        let parsed = parse(&record).unwrap();
+        assert_eq!(parsed.advisory.cves, ["CVE-2023-44487"]);
+        assert_eq!(parsed.advisory.score, Some(7.5));
+        assert_eq!(parsed.advisory.severity.as_deref(), Some("high"));
+        assert_eq!(parsed.advisory.summary, "HTTP/2 rapid reset");
+        let package = &parsed.packages[0];
+        assert_eq!(package.ecosystem, "Ubuntu:22.04");
+        assert_eq!(package.ranges.len(), 1);
+        assert_eq!(package.versions.len(), 2);
+    }
+
+    #[test]
+    fn test_parse_events() {
+        let events = json!([
+            { "introduced": "1.0" },
+            { "fixed": "1.2" },
+            { "introduced": "2.0" },
+            { "last_affected": "2.3" },
+            { "introduced": "3.0" }
+        ]);
+        let ranges = parse_events(events.as_array().unwrap());
+        assert_eq!(ranges.len(), 3);
+        assert_eq!(ranges[1].last_affected.as_deref(), Some("2.3"));
+        assert_eq!(ranges[2].introduced.as_deref(), Some("3.0"));
+        assert_eq!(ranges[2].fixed, None);
+    }
+
+    #[test]
+    fn test_withdrawn() {
+        let record = json!({
+            "id": "ALSA-2024:0001",
+            "withdrawn": "2024-02-01T00:00:00Z",
+            "affected": [{
+                "package": { "ecosystem": "AlmaLinux:9", "name": "openssl" },
+                "ranges": [{ "type": "ECOSYSTEM", "events": [{ "introduced": "0" }, { "fixed": "1:3.0.7-25.el9" }] }]
+            }]
+        });
+        assert!(parse(&record).is_none());
+    }
+}
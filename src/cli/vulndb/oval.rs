@@ -0,0 +1,338 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! OVAL definitions, as Red Hat, Debian, Ubuntu and Oracle publish them
+//!
+//! Only the `<package> is earlier than <version>` tests of a definition are
+//! kept, each as a package fixed in that version. Tests of the release, the
+//! signing key or module streams are left out; the feed is for one release.
+
+use super::version::Scheme;
+use super::{cvss, severity_name, Advisory, AffectedPackage, Range, Record};
+use anyhow::{Context, Result};
+use roxmltree::{Document, Node, ParsingOptions};
+use std::collections::HashMap;
+
+/// A `<package> is earlier than <evr>` test
+struct VersionTest<'a> {
+    scheme: Scheme,
+    object: &'a str,
+    state: &'a str,
+}
+
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|c| c.tag_name().name() == name)
+}
+
+fn text<'a>(node: Node<'a, '_>) -> &'a str {
+    node.text().unwrap_or_default().trim()
+}
+
+/// The definitions of an OVAL document, for packages of `ecosystem`
+pub(super) fn parse(xml: &str, ecosystem: &str) -> Result<Vec<Record>> {
+    let options = ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    let doc = Document::parse_with_options(xml, options).context("Invalid OVAL document")?;
+
+    let mut tests = HashMap::new();
+    let mut objects: HashMap<&str, Node> = HashMap::new();
+    let mut states = HashMap::new();
+    let mut variables: HashMap<&str, Vec<&str>> = HashMap::new();
+    for node in doc.descendants().filter(Node::is_element) {
+        let Some(id) = node.attribute("id") else {
+            continue;
+        };
+        match node.tag_name().name() {
+            "rpminfo_test" | "dpkginfo_test" => {
+                let object = child(node, "object").and_then(|o| o.attribute("object_ref"));
+                let state = child(node, "state").and_then(|s| s.attribute("state_ref"));
+                let (Some(object), Some(state)) = (object, state) else {
+                    continue;
+                };
+                let scheme = if node.tag_name().name() == "rpminfo_test" {
+                    Scheme::Rpm
+                } else {
+                    Scheme::Dpkg
+                };
+                tests.insert(
+                    id,
+                    VersionTest {
+                        scheme,
+                        object,
+                        state,
+                    },
+                );
+            }
+            "rpminfo_object" | "dpkginfo_object" => {
+                objects.insert(id, node);
+            }
+            "rpminfo_state" | "dpkginfo_state" => {
+                if let Some(evr) =
+                    child(node, "evr").filter(|evr| evr.attribute("operation") == Some("less than"))
+                {
+                    states.insert(id, text(evr));
+                }
+            }
+            "constant_variable" => {
+                let values = node
+                    .children()
+                    .filter(|c| c.tag_name().name() == "value")
+                    .map(text)
+                    .collect();
+                variables.insert(id, values);
+            }
+            _ => {}
+        }
+    }
+
+    // Package names of an object, given directly or as a variable
+    let names = |object: &str| -> Vec<&str> {
+        let Some(name) = objects.get(object).and_then(|o| child(*o, "name")) else {
+            return Vec::new();
+        };
+        match name.attribute("var_ref") {
+            Some(variable) => variables.get(variable).cloned().unwrap_or_default(),
+            None => vec![text(name)],
+        }
+    };
+
+    let mut records = Vec::new();
+    for definition in doc
+        .descendants()
+        .filter(|n| n.tag_name().name() == "definition")
+    {
+        if !matches!(
+            definition.attribute("class"),
+            Some("patch" | "vulnerability")
+        ) {
+            continue;
+        }
+
+        let mut packages: Vec<AffectedPackage> = Vec::new();
+        let criteria = definition
+            .descendants()
+            .filter(|n| n.tag_name().name() == "criterion")
+            .filter_map(|n| tests.get(n.attribute("test_ref")?));
+        for test in criteria {
+            let Some(fixed) = states.get(test.state) else {
+                continue;
+            };
+            for name in names(test.object) {
+                let range = Range {
+                    introduced: None,
+                    fixed: Some(fixed.to_string()),
+                    last_affected: None,
+                };
+                let known = packages
+                    .iter()
+                    .any(|p| p.name == name && p.ranges[0] == range);
+                if !known && !name.is_empty() {
+                    packages.push(AffectedPackage {
+                        ecosystem: ecosystem.to_string(),
+                        name: name.to_string(),
+                        scheme: test.scheme,
+                        ranges: vec![range],
+                        versions: Vec::new(),
+                    });
+                }
+            }
+        }
+        if packages.is_empty() {
+            continue;
+        }
+
+        let advisory = advisory(definition);
+        records.push(Record { advisory, packages });
+    }
+    Ok(records)
+}
+
+fn advisory(definition: Node) -> Advisory {
+    let metadata = child(definition, "metadata");
+    let mut cves: Vec<String> = Vec::new();
+    let mut id = None;
+    let mut score: Option<f64> = None;
+    let mut severity = None;
+
+    if let Some(metadata) = metadata {
+        for reference in metadata
+            .children()
+            .filter(|c| c.tag_name().name() == "reference")
+        {
+            let Some(ref_id) = reference.attribute("ref_id") else {
+                continue;
+            };
+            if reference.attribute("source") == Some("CVE") {
+                cves.push(ref_id.to_string());
+            } else if id.is_none() {
+                id = Some(ref_id.to_string());
+            }
+        }
+        if let Some(advisory) = child(metadata, "advisory") {
+            severity = child(advisory, "severity").and_then(|s| severity_name(text(s)));
+            for cve in advisory.children().filter(|c| c.tag_name().name() == "cve") {
+                cves.push(text(cve).to_string());
+                // Red Hat writes `7.5/CVSS:3.1/AV:N/...`
+                let cve_score = cve.attribute("cvss3").and_then(|cvss| {
+                    let (score, vector) = cvss.split_once('/')?;
+                    score.parse().ok().or_else(|| cvss::base_score(vector))
+                });
+                if let Some(cve_score) = cve_score {
+                    score = Some(score.map_or(cve_score, |known| known.max(cve_score)));
+                }
+            }
+        }
+    }
+    cves.retain(|cve| cve.starts_with("CVE-"));
+    cves.sort();
+    cves.dedup();
+
+    let title = metadata
+        .and_then(|m| child(m, "title"))
+        .map(text)
+        .unwrap_or_default();
+    Advisory {
+        id: id
+            .or_else(|| cves.first().cloned())
+            .or_else(|| definition.attribute("id").map(str::to_string))
+            .unwrap_or_default(),
+        severity: severity
+            .or_else(|| score.map(cvss::rating))
+            .map(str::to_string),
+        score,
+        summary: title.to_string(),
+        cves,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RHEL: &str = r#"<?xml version="1.0" encoding="utf-8"?>
+<oval_definitions xmlns="http://oval.mitre.org/XMLSchema/oval-definitions-5"
+    xmlns:red-def="http://oval.mitre.org/XMLSchema/oval-definitions-5#linux">
+  <definitions>
+    <definition class="patch" id="oval:com.redhat.rhsa:def:20240001" version="636">
+      <metadata>
+        <title>RHSA-2024:0001: openssl security update (Moderate)</title>
+        <reference ref_id="RHSA-2024:0001" ref_url="https://access.redhat.com/errata/RHSA-2024:0001" source="RHSA"/>
+        <reference ref_id="CVE-2023-5678" ref_url="https://access.redhat.com/security/cve/CVE-2023-5678" source="CVE"/>
+        <advisory from="secalert@redhat.com">
+          <severity>Moderate</severity>
+          <cve cvss3="5.3/CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:N/I:N/A:L" href="https://access.redhat.com/security/cve/CVE-2023-5678">CVE-2023-5678</cve>
+          <cve cvss3="7.5/CVSS:3.1/AV:N/AC:L/PR:N/UI:N/S:U/C:N/I:N/A:H">CVE-2024-0727</cve>
+        </advisory>
+      </metadata>
+      <criteria operator="OR">
+        <criterion comment="Red Hat Enterprise Linux must be installed" test_ref="oval:com.redhat.rhba:tst:20191992005"/>
+        <criteria operator="AND">
+          <criterion comment="openssl is earlier than 1:3.0.7-25.el9_3" test_ref="oval:com.redhat.rhsa:tst:20240001001"/>
+          <criterion comment="openssl is signed with Red Hat redhatrelease2 key" test_ref="oval:com.redhat.rhsa:tst:20240001002"/>
+          <criterion comment="openssl-libs is earlier than 1:3.0.7-25.el9_3" test_ref="oval:com.redhat.rhsa:tst:20240001003"/>
+        </criteria>
+      </criteria>
+    </definition>
+  </definitions>
+  <tests>
+    <red-def:rpminfo_test check="at least one" id="oval:com.redhat.rhsa:tst:20240001001" version="636">
+      <red-def:object object_ref="oval:com.redhat.rhsa:obj:20240001001"/>
+      <red-def:state state_ref="oval:com.redhat.rhsa:ste:20240001001"/>
+    </red-def:rpminfo_test>
+    <red-def:rpminfo_test check="at least one" id="oval:com.redhat.rhsa:tst:20240001002" version="636">
+      <red-def:object object_ref="oval:com.redhat.rhsa:obj:20240001001"/>
+      <red-def:state state_ref="oval:com.redhat.rhba:ste:20191992002"/>
+    </red-def:rpminfo_test>
+    <red-def:rpminfo_test check="at least one" id="oval:com.redhat.rhsa:tst:20240001003" version="636">
+      <red-def:object object_ref="oval:com.redhat.rhsa:obj:20240001003"/>
+      <red-def:state state_ref="oval:com.redhat.rhsa:ste:20240001001"/>
+    </red-def:rpminfo_test>
+  </tests>
+  <objects>
+    <red-def:rpminfo_object id="oval:com.redhat.rhsa:obj:20240001001" version="636">
+      <red-def:name>openssl</red-def:name>
+    </red-def:rpminfo_object>
+    <red-def:rpminfo_object id="oval:com.redhat.rhsa:obj:20240001003" version="636">
+      <red-def:name>openssl-libs</red-def:name>
+    </red-def:rpminfo_object>
+  </objects>
+  <states>
+    <red-def:rpminfo_state id="oval:com.redhat.rhsa:ste:20240001001" version="636">
+      <red-def:arch datatype="string" operation="pattern match">aarch64|x86_64</red-def:arch>
+      <red-def:evr datatype="evr_string" operation="less than">1:3.0.7-25.el9_3</red-def:evr>
+    </red-def:rpminfo_state>
+    <red-def:rpminfo_state id="oval:com.redhat.rhba:ste:20191992002" version="636">
+      <red-def:signature_keyid operation="equals">199e2f91fd431d51</red-def:signature_keyid>
+    </red-def:rpminfo_state>
+  </states>
+</oval_definitions>"#;
+
+    const UBUNTU: &str = r#"<oval_definitions xmlns="http://oval.mitre.org/XMLSchema/oval-definitions-5"
+    xmlns:linux-def="http://oval.mitre.org/XMLSchema/oval-definitions-5#linux">
+  <definitions>
+    <definition class="vulnerability" id="oval:com.ubuntu.jammy:def:202344487000000" version="1">
+      <metadata>
+        <title>CVE-2023-44487 on Ubuntu 22.04 LTS (jammy) - high</title>
+        <reference source="CVE" ref_id="CVE-2023-44487" ref_url="https://ubuntu.com/security/CVE-2023-44487"/>
+        <advisory>
+          <severity>High</severity>
+        </advisory>
+      </metadata>
+      <criteria>
+        <criterion test_ref="oval:com.ubuntu.jammy:tst:202344487000000" comment="nginx package in jammy was vulnerable but has been fixed"/>
+      </criteria>
+    </definition>
+  </definitions>
+  <tests>
+    <linux-def:dpkginfo_test id="oval:com.ubuntu.jammy:tst:202344487000000" version="1" check_existence="at_least_one_exists" check="at least one">
+      <linux-def:object object_ref="oval:com.ubuntu.jammy:obj:202344487000000"/>
+      <linux-def:state state_ref="oval:com.ubuntu.jammy:ste:202344487000000"/>
+    </linux-def:dpkginfo_test>
+  </tests>
+  <objects>
+    <linux-def:dpkginfo_object id="oval:com.ubuntu.jammy:obj:202344487000000" version="1">
+      <linux-def:name var_ref="oval:com.ubuntu.jammy:var:202344487000000" var_check="at least one"/>
+    </linux-def:dpkginfo_object>
+  </objects>
+  <states>
+    <linux-def:dpkginfo_state id="oval:com.ubuntu.jammy:ste:202344487000000" version="1">
+      <linux-def:evr datatype="debian_evr_string" operation="less than">0:1.18.0-6ubuntu14.5</linux-def:evr>
+    </linux-def:dpkginfo_state>
+  </states>
+  <variables>
+    <constant_variable id="oval:com.ubuntu.jammy:var:202344487000000" version="1" datatype="string">
+      <value>nginx</value>
+      <value>nginx-core</value>
+    </constant_variable>
+  </variables>
+</oval_definitions>"#;
+
+    #[test]
+    fn test_parse_rhel() {
+        let records = parse(RHEL, "Red Hat:9").unwrap();
+        assert_eq!(records.len(), 1);
+        let record = &records[0];
+        assert_eq!(record.advisory.id, "RHSA-2024:0001");
+        assert_eq!(record.advisory.cves, ["CVE-2023-5678", "CVE-2024-0727"]);
+        assert_eq!(record.advisory.severity.as_deref(), Some("medium"));
+        assert_eq!(record.advisory.score, Some(7.5));
+        let names: Vec<_> = record.packages.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["openssl", "openssl-libs"]);
+        assert_eq!(record.packages[0].scheme, Scheme::Rpm);
+        assert_eq!(
+            record.packages[0].ranges[0].fixed.as_deref(),
+            Some("1:3.0.7-25.el9_3")
+        );
+    }
+
+    #[test]
+    fn test_parse_ubuntu_variables() {
+        let records = parse(UBUNTU, "Ubuntu:22.04").unwrap();
+        let record = &records[0];
+        assert_eq!(record.advisory.id, "CVE-2023-44487");
+        assert_eq!(record.advisory.severity.as_deref(), Some("high"));
+        let names: Vec<_> = record.packages.iter().map(|p| p.name.as_str()).collect();
+        assert_eq!(names, ["nginx", "nginx-core"]);
+        assert_eq!(record.packages[0].scheme, Scheme::Dpkg);
+    }
+}
@@ -0,0 +1,385 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Version ordering of the distro package managers
+
+use serde::{Deserialize, Serialize};
+use std::cmp::Ordering;
+
+/// How an ecosystem orders its versions
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Scheme {
+    /// dpkg, `[epoch:]upstream[-revision]`
+    Dpkg,
+    /// RPM, `[epoch:]version-release`
+    Rpm,
+    /// apk, `1.2.3[a][_suffix][-rN]`
+    Apk,
+    /// Runs of digits and letters, as rpmvercmp compares them
+    Generic,
+}
+
+impl Scheme {
+    /// The scheme of an OSV ecosystem, such as `Debian:12`
+    pub fn of(ecosystem: &str) -> Self {
+        let family = ecosystem.split(':').next().unwrap_or_default();
+        match family {
+            "Debian" | "Ubuntu" => Self::Dpkg,
+            "Alpine" | "Wolfi" | "Chainguard" => Self::Apk,
+            "AlmaLinux" | "Rocky Linux" | "Red Hat" | "Oracle Linux" | "openSUSE" | "SUSE"
+            | "Mageia" | "openEuler" => Self::Rpm,
+            _ => Self::Generic,
+        }
+    }
+
+    pub fn compare(self, a: &str, b: &str) -> Ordering {
+        match self {
+            Self::Dpkg => dpkg_compare(a, b),
+            Self::Rpm => rpm_compare(a, b),
+            Self::Apk => apk_compare(a, b),
+            Self::Generic => rpmvercmp(a, b),
+        }
+    }
+}
+
+/// Split off a numeric `epoch:` prefix
+fn split_epoch(version: &str) -> (u64, &str) {
+    match version.split_once(':') {
+        Some((epoch, rest)) if !epoch.is_empty() && epoch.bytes().all(|b| b.is_ascii_digit()) => {
+            (epoch.parse().unwrap_or(0), rest)
+        }
+        _ => (0, version),
+    }
+}
+
+fn dpkg_compare(a: &str, b: &str) -> Ordering {
+    let (a_epoch, a) = split_epoch(a);
+    let (b_epoch, b) = split_epoch(b);
+    let (a_upstream, a_revision) = a.rsplit_once('-').unwrap_or((a, ""));
+    let (b_upstream, b_revision) = b.rsplit_once('-').unwrap_or((b, ""));
+    a_epoch
+        .cmp(&b_epoch)
+        .then_with(|| verrevcmp(a_upstream, b_upstream))
+        .then_with(|| verrevcmp(a_revision, b_revision))
+}
+
+/// dpkg's weight of a non-digit: `~` sorts before everything, even the end
+fn dpkg_order(c: Option<u8>) -> i32 {
+    match c {
+        None => 0,
+        Some(b'~') => -1,
+        Some(c) if c.is_ascii_digit() => 0,
+        Some(c) if c.is_ascii_alphabetic() => c as i32,
+        Some(c) => c as i32 + 256,
+    }
+}
+
+/// dpkg's comparison of an upstream version or a revision
+fn verrevcmp(a: &str, b: &str) -> Ordering {
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let (mut i, mut j) = (0, 0);
+    let digit = |s: &[u8], k: usize| s.get(k).is_some_and(u8::is_ascii_digit);
+
+    while i < a.len() || j < b.len() {
+        while (i < a.len() && !digit(a, i)) || (j < b.len() && !digit(b, j)) {
+            let (ac, bc) = (dpkg_order(a.get(i).copied()), dpkg_order(b.get(j).copied()));
+            if ac != bc {
+                return ac.cmp(&bc);
+            }
+            i += 1;
+            j += 1;
+        }
+        while a.get(i) == Some(&b'0') {
+            i += 1;
+        }
+        while b.get(j) == Some(&b'0') {
+            j += 1;
+        }
+        let mut first_diff = Ordering::Equal;
+        while digit(a, i) && digit(b, j) {
+            if first_diff == Ordering::Equal {
+                first_diff = a[i].cmp(&b[j]);
+            }
+            i += 1;
+            j += 1;
+        }
+        if digit(a, i) {
+            return Ordering::Greater;
+        }
+        if digit(b, j) {
+            return Ordering::Less;
+        }
+        if first_diff != Ordering::Equal {
+            return first_diff;
+        }
+    }
+    Ordering::Equal
+}
+
+fn rpm_compare(a: &str, b: &str) -> Ordering {
+    let (a_epoch, a) = split_epoch(a);
+    let (b_epoch, b) = split_epoch(b);
+    let (a_version, a_release) = a.rsplit_once('-').unwrap_or((a, ""));
+    let (b_version, b_release) = b.rsplit_once('-').unwrap_or((b, ""));
+    let order = a_epoch
+        .cmp(&b_epoch)
+        .then_with(|| rpmvercmp(a_version, b_version));
+    // A version without a release matches every release of it
+    if order != Ordering::Equal || a_release.is_empty() || b_release.is_empty() {
+        return order;
+    }
+    rpmvercmp(a_release, b_release)
+}
+
+/// RPM's segment-wise comparison, with `~` sorting before and `^` after
+/// the end of a version
+fn rpmvercmp(a: &str, b: &str) -> Ordering {
+    if a == b {
+        return Ordering::Equal;
+    }
+    let (a, b) = (a.as_bytes(), b.as_bytes());
+    let (mut i, mut j) = (0, 0);
+    let separator = |c: &u8| !c.is_ascii_alphanumeric() && *c != b'~' && *c != b'^';
+
+    loop {
+        while a.get(i).is_some_and(separator) {
+            i += 1;
+        }
+        while b.get(j).is_some_and(separator) {
+            j += 1;
+        }
+
+        let (ac, bc) = (a.get(i).copied(), b.get(j).copied());
+        if ac == Some(b'~') || bc == Some(b'~') {
+            if ac != Some(b'~') {
+                return Ordering::Greater;
+            }
+            if bc != Some(b'~') {
+                return Ordering::Less;
+            }
+            i += 1;
+            j += 1;
+            continue;
+        }
+        if ac == Some(b'^') || bc == Some(b'^') {
+            if ac.is_none() {
+                return Ordering::Less;
+            }
+            if bc.is_none() {
+                return Ordering::Greater;
+            }
+            if ac != Some(b'^') {
+                return Ordering::Greater;
+            }
+            if bc != Some(b'^') {
+                return Ordering::Less;
+            }
+            i += 1;
+            j += 1;
+            continue;
+        }
+        if ac.is_none() || bc.is_none() {
+            break;
+        }
+
+        let numeric = a[i].is_ascii_digit();
+        let class = |c: &u8| {
+            if numeric {
+                c.is_ascii_digit()
+            } else {
+                c.is_ascii_alphabetic()
+            }
+        };
+        let a_start = i;
+        while a.get(i).is_some_and(class) {
+            i += 1;
+        }
+        let b_start = j;
+        while b.get(j).is_some_and(class) {
+            j += 1;
+        }
+        let (a_seg, b_seg) = (&a[a_start..i], &b[b_start..j]);
+
+        // A number is newer than letters
+        if b_seg.is_empty() {
+            return if numeric {
+                Ordering::Greater
+            } else {
+                Ordering::Less
+            };
+        }
+        let order = if numeric {
+            let trim = |s: &[u8]| -> usize { s.iter().take_while(|&&c| c == b'0').count() };
+            let (a_seg, b_seg) = (&a_seg[trim(a_seg)..], &b_seg[trim(b_seg)..]);
+            a_seg.len().cmp(&b_seg.len()).then_with(|| a_seg.cmp(b_seg))
+        } else {
+            a_seg.cmp(b_seg)
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+
+    match (i >= a.len(), j >= b.len()) {
+        (true, true) => Ordering::Equal,
+        (true, false) => Ordering::Less,
+        _ => Ordering::Greater,
+    }
+}
+
+/// An apk version, split into what apk orders by
+#[derive(Debug, Default)]
+struct ApkVersion<'a> {
+    numbers: Vec<&'a str>,
+    letter: Option<u8>,
+    /// Rank and number of each `_suffix`
+    suffixes: Vec<(i8, u64)>,
+    revision: u64,
+}
+
+/// Suffixes before a release rank below zero, those after above
+fn apk_suffix_rank(suffix: &str) -> i8 {
+    match suffix {
+        "alpha" => -4,
+        "beta" => -3,
+        "pre" => -2,
+        "rc" => -1,
+        "cvs" => 1,
+        "svn" => 2,
+        "git" => 3,
+        "hg" => 4,
+        "p" => 5,
+        _ => 0,
+    }
+}
+
+fn parse_apk(version: &str) -> ApkVersion<'_> {
+    let (version, revision) = match version.rsplit_once("-r") {
+        Some((version, revision)) if revision.bytes().all(|b| b.is_ascii_digit()) => {
+            (version, revision.parse().unwrap_or(0))
+        }
+        _ => (version, 0),
+    };
+    // A `~<commit>` suffix does not order
+    let version = version.split('~').next().unwrap_or_default();
+    let mut parts = version.split('_');
+    let release = parts.next().unwrap_or_default();
+
+    let mut parsed = ApkVersion {
+        revision,
+        ..Default::default()
+    };
+    for number in release.split('.') {
+        match number.bytes().position(|b| !b.is_ascii_digit()) {
+            Some(at) => {
+                parsed.numbers.push(&number[..at]);
+                parsed.letter = number.as_bytes().get(at).copied();
+            }
+            None => parsed.numbers.push(number),
+        }
+    }
+    for suffix in parts {
+        let at = suffix
+            .bytes()
+            .position(|b| b.is_ascii_digit())
+            .unwrap_or(suffix.len());
+        parsed.suffixes.push((
+            apk_suffix_rank(&suffix[..at]),
+            suffix[at..].parse().unwrap_or(0),
+        ));
+    }
+    parsed
+}
+
+fn apk_compare(a: &str, b: &str) -> Ordering {
+    let (a, b) = (parse_apk(a), parse_apk(b));
+    for k in 0..a.numbers.len().max(b.numbers.len()) {
+        let order = match (a.numbers.get(k), b.numbers.get(k)) {
+            (Some(x), Some(y)) => rpmvercmp(x, y),
+            (Some(_), None) => Ordering::Greater,
+            (None, Some(_)) => Ordering::Less,
+            (None, None) => Ordering::Equal,
+        };
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+    let order = a.letter.cmp(&b.letter);
+    if order != Ordering::Equal {
+        return order;
+    }
+    for k in 0..a.suffixes.len().max(b.suffixes.len()) {
+        let x = a.suffixes.get(k).copied().unwrap_or_default();
+        let y = b.suffixes.get(k).copied().unwrap_or_default();
+        let order = x.cmp(&y);
+        if order != Ordering::Equal {
+            return order;
+        }
+    }
+    a.revision.cmp(&b.revision)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use Ordering::*;
+
+    #[test]
+    fn test_dpkg_compare() {
+        let cases = [
+            ("1.0", "1.0", Equal),
+            ("1.0-1", "1.0-2", Less),
+            ("1:1.0", "2.0", Greater),
+            ("1.0~rc1", "1.0", Less),
+            ("1.0~rc1-1", "1.0~~", Greater),
+            ("3.0.11-1~deb12u2", "3.0.11-1~deb12u2+b1", Less),
+            ("2.36-9+deb12u4", "2.36-9+deb12u10", Less),
+            ("1.0a", "1.0+", Less),
+            ("0:1.2.11.dfsg-2ubuntu9.2", "1.2.11.dfsg-2ubuntu9", Greater),
+            ("1.01", "1.1", Equal),
+        ];
+        for (a, b, expected) in cases {
+            assert_eq!(Scheme::Dpkg.compare(a, b), expected, "{} vs {}", a, b);
+        }
+    }
+
+    #[test]
+    fn test_rpm_compare() {
+        let cases = [
+            ("0:3.0.7-24.el9", "3.0.7-25.el9", Less),
+            ("1:1.0-1", "2.0-1", Greater),
+            ("1.0~rc1-1", "1.0-1", Less),
+            ("1.0^git1-1", "1.0-1", Greater),
+            ("1.0^git1-1", "1.0.1-1", Less),
+            ("5.14.0-362.8.1.el9_3", "5.14.0-362.18.1.el9_3", Less),
+            ("2.a", "2.1", Less),
+            ("1.0010", "1.9", Greater),
+            ("1.0", "1.0-5.el9", Equal),
+        ];
+        for (a, b, expected) in cases {
+            assert_eq!(Scheme::Rpm.compare(a, b), expected, "{} vs {}", a, b);
+        }
+    }
+
+    #[test]
+    fn test_apk_compare() {
+        let cases = [
+            ("1.2.4-r2", "1.2.4-r10", Less),
+            ("1.36.1-r2", "1.36.1", Greater),
+            ("3.1.4_rc1-r0", "3.1.4-r0", Less),
+            ("3.1.4_p1-r0", "3.1.4-r5", Greater),
+            ("1.2.3a-r0", "1.2.3-r0", Greater),
+            ("1.2-r0", "1.2.1-r0", Less),
+            ("2.0_alpha2", "2.0_beta1", Less),
+        ];
+        for (a, b, expected) in cases {
+            assert_eq!(Scheme::Apk.compare(a, b), expected, "{} vs {}", a, b);
+        }
+    }
+
+    #[test]
+    fn test_scheme_of() {
+        assert_eq!(Scheme::of("Debian:12"), Scheme::Dpkg);
+        assert_eq!(Scheme::of("Alpine:v3.19"), Scheme::Apk);
+        assert_eq!(Scheme::of("Rocky Linux:9"), Scheme::Rpm);
+        assert_eq!(Scheme::of("PyPI"), Scheme::Generic);
+    }
+}
@@ -0,0 +1,482 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! OpenVEX documents, <https://github.com/openvex/spec>
+//!
+//! `patch --export-vex` writes one `affected` statement per vulnerable
+//! package. Reviewed documents are read back with `--vex`: findings whose
+//! latest statement is `not_affected` or `fixed` are left out of reports.
+
+use anyhow::{bail, Context, Result};
+use chrono::{DateTime, FixedOffset, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::path::PathBuf;
+use uuid::Uuid;
+
+pub const CONTEXT: &str = "https://openvex.dev/ns/v0.2.0";
+
+/// An OpenVEX document
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct VexDocument {
+    #[serde(rename = "@context")]
+    pub context: String,
+    #[serde(rename = "@id")]
+    pub id: String,
+    pub author: String,
+    pub timestamp: String,
+    pub version: u32,
+    #[serde(default)]
+    pub statements: Vec<Statement>,
+}
+
+impl VexDocument {
+    pub fn new(author: &str, statements: Vec<Statement>) -> Self {
+        Self {
+            context: CONTEXT.to_string(),
+            id: format!("urn:uuid:{}", Uuid::new_v4()),
+            author: author.to_string(),
+            timestamp: Utc::now().to_rfc3339(),
+            version: 1,
+            statements,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Status {
+    NotAffected,
+    Affected,
+    Fixed,
+    UnderInvestigation,
+}
+
+/// Whether the products are affected by one vulnerability
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Statement {
+    pub vulnerability: Vulnerability,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub products: Vec<Product>,
+    pub status: Status,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub justification: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub impact_statement: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub action_statement: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub timestamp: Option<String>,
+}
+
+impl Statement {
+    /// An `affected` statement for an installed package
+    pub fn affected(cve: &str, purl: &str, name: &str, fixed: Option<&str>) -> Self {
+        let action = match fixed {
+            Some(fixed) => format!("Update {} to {} or later", name, fixed),
+            None => "No fix is available yet".to_string(),
+        };
+        Self {
+            vulnerability: Vulnerability {
+                name: cve.to_string(),
+                aliases: Vec::new(),
+            },
+            products: vec![Product {
+                id: purl.to_string(),
+                ..Default::default()
+            }],
+            status: Status::Affected,
+            justification: None,
+            impact_statement: None,
+            action_statement: Some(action),
+            timestamp: None,
+        }
+    }
+
+    fn names(&self, cve: &str) -> bool {
+        self.vulnerability.name == cve || self.vulnerability.aliases.iter().any(|a| a == cve)
+    }
+
+    /// Whether the statement covers the package `purl`; one without
+    /// products covers every package
+    fn covers(&self, purl: &Purl) -> bool {
+        if self.products.is_empty() {
+            return true;
+        }
+        self.products.iter().any(|product| {
+            product
+                .ids()
+                .chain(product.subcomponents.iter().flat_map(Product::ids))
+                .filter_map(Purl::parse)
+                .any(|id| id.matches(purl))
+        })
+    }
+}
+
+/// A vulnerability, named by an object or, before OpenVEX 0.2, a string
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(from = "VulnerabilityRef")]
+pub struct Vulnerability {
+    pub name: String,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub aliases: Vec<String>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum VulnerabilityRef {
+    Name(String),
+    Object {
+        name: String,
+        #[serde(default)]
+        aliases: Vec<String>,
+    },
+}
+
+impl From<VulnerabilityRef> for Vulnerability {
+    fn from(vulnerability: VulnerabilityRef) -> Self {
+        match vulnerability {
+            VulnerabilityRef::Name(name) => Self {
+                name,
+                aliases: Vec::new(),
+            },
+            VulnerabilityRef::Object { name, aliases } => Self { name, aliases },
+        }
+    }
+}
+
+/// A product or subcomponent, named by a package URL
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(from = "ProductRef")]
+pub struct Product {
+    #[serde(rename = "@id")]
+    pub id: String,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub identifiers: BTreeMap<String, String>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub subcomponents: Vec<Product>,
+}
+
+impl Product {
+    fn ids(&self) -> impl Iterator<Item = &str> {
+        std::iter::once(self.id.as_str()).chain(self.identifiers.get("purl").map(String::as_str))
+    }
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum ProductRef {
+    Id(String),
+    Object {
+        #[serde(rename = "@id", default)]
+        id: String,
+        #[serde(default)]
+        identifiers: BTreeMap<String, String>,
+        #[serde(default)]
+        subcomponents: Vec<Product>,
+    },
+}
+
+impl From<ProductRef> for Product {
+    fn from(product: ProductRef) -> Self {
+        match product {
+            ProductRef::Id(id) => Self {
+                id,
+                ..Default::default()
+            },
+            ProductRef::Object {
+                id,
+                identifiers,
+                subcomponents,
+            } => Self {
+                id,
+                identifiers,
+                subcomponents,
+            },
+        }
+    }
+}
+
+/// The statements of one or more VEX documents, oldest first
+#[derive(Debug, Default)]
+pub struct Vex {
+    statements: Vec<(Option<DateTime<FixedOffset>>, Statement)>,
+}
+
+impl Vex {
+    /// Read VEX documents; where statements disagree the latest one wins
+    pub fn load(paths: &[PathBuf]) -> Result<Self> {
+        let mut vex = Self::default();
+        for path in paths {
+            let content = std::fs::read_to_string(path)
+                .with_context(|| format!("Failed to read VEX document {}", path.display()))?;
+            let document = parse(&content)
+                .with_context(|| format!("Invalid VEX document {}", path.display()))?;
+            vex.add(document);
+        }
+        Ok(vex)
+    }
+
+    pub fn add(&mut self, document: VexDocument) {
+        for statement in document.statements {
+            let timestamp = statement
+                .timestamp
+                .as_deref()
+                .unwrap_or(&document.timestamp);
+            let timestamp = DateTime::parse_from_rfc3339(timestamp).ok();
+            self.statements.push((timestamp, statement));
+        }
+        // Stable, so documents given later win ties
+        self.statements.sort_by_key(|(timestamp, _)| *timestamp);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.statements.is_empty()
+    }
+
+    /// The latest statement about `cve` in the package `purl`
+    pub fn status(&self, cve: &str, purl: &str) -> Option<&Statement> {
+        let purl = Purl::parse(purl)?;
+        self.statements
+            .iter()
+            .rev()
+            .map(|(_, statement)| statement)
+            .find(|statement| statement.names(cve) && statement.covers(&purl))
+    }
+
+    /// Whether the package `purl` is stated not to be affected by `cve`
+    pub fn suppresses(&self, cve: &str, purl: &str) -> bool {
+        matches!(
+            self.status(cve, purl).map(|statement| statement.status),
+            Some(Status::NotAffected | Status::Fixed)
+        )
+    }
+}
+
+/// Parse an OpenVEX document
+pub fn parse(json: &str) -> Result<VexDocument> {
+    let document: VexDocument = serde_json::from_str(json)?;
+    if !document.context.starts_with("https://openvex.dev/ns") {
+        bail!("Not an OpenVEX document: @context is {}", document.context);
+    }
+    Ok(document)
+}
+
+/// Package URL of an installed package
+///
+/// `package_type` is the package URL type (`deb`, `rpm`, `apk`, `alpm`,
+/// `ebuild`) and `namespace` the distro, as `inspect` names it.
+pub fn package_purl(
+    package_type: &str,
+    namespace: &str,
+    name: &str,
+    version: &str,
+    arch: &str,
+) -> String {
+    let mut purl = format!("pkg:{}/", package_type);
+    if !namespace.is_empty() {
+        purl.push_str(&encode(namespace));
+        purl.push('/');
+    }
+    purl.push_str(&encode(name));
+    if !version.is_empty() {
+        purl.push('@');
+        purl.push_str(&encode(version));
+    }
+    if !arch.is_empty() {
+        purl.push_str("?arch=");
+        purl.push_str(&encode(arch));
+    }
+    purl
+}
+
+fn encode(text: &str) -> String {
+    let mut encoded = String::new();
+    for byte in text.bytes() {
+        if byte.is_ascii_alphanumeric() || b".-_~".contains(&byte) {
+            encoded.push(byte as char);
+        } else {
+            encoded.push_str(&format!("%{:02X}", byte));
+        }
+    }
+    encoded
+}
+
+fn decode(text: &str) -> String {
+    let bytes = text.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        let hex = bytes
+            .get(i + 1..i + 3)
+            .and_then(|hex| std::str::from_utf8(hex).ok())
+            .and_then(|hex| u8::from_str_radix(hex, 16).ok());
+        match (bytes[i], hex) {
+            (b'%', Some(byte)) => {
+                decoded.push(byte);
+                i += 3;
+            }
+            (byte, _) => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8_lossy(&decoded).into_owned()
+}
+
+/// The parts of a package URL products are matched on
+#[derive(Debug, PartialEq)]
+struct Purl {
+    package_type: String,
+    namespace: Option<String>,
+    name: String,
+    version: Option<String>,
+}
+
+impl Purl {
+    fn parse(purl: &str) -> Option<Self> {
+        let rest = purl.strip_prefix("pkg:")?;
+        let rest = rest.split('#').next()?;
+        let rest = rest.split('?').next()?;
+        let (path, version) = match rest.rsplit_once('@') {
+            Some((path, version)) => (path, Some(decode(version))),
+            None => (rest, None),
+        };
+        let mut segments: Vec<&str> = path.trim_matches('/').split('/').collect();
+        if segments.len() < 2 {
+            return None;
+        }
+        let package_type = segments.remove(0).to_lowercase();
+        let name = decode(segments.pop()?);
+        let namespace = (!segments.is_empty()).then(|| decode(&segments.join("/")));
+        Some(Self {
+            package_type,
+            namespace,
+            name,
+            version,
+        })
+    }
+
+    /// Whether a product names `package`: the namespace and version must
+    /// agree only where the product gives them
+    fn matches(&self, package: &Purl) -> bool {
+        let namespace = match (&self.namespace, &package.namespace) {
+            (Some(mine), Some(theirs)) => mine.eq_ignore_ascii_case(theirs),
+            (mine, _) => mine.is_none(),
+        };
+        let version = match (&self.version, &package.version) {
+            (Some(mine), Some(theirs)) => mine == theirs,
+            (mine, _) => mine.is_none(),
+        };
+        self.package_type == package.package_type
+            && self.name == package.name
+            && namespace
+            && version
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const TRIAGE: &str = r#"{
+      "@context": "https://openvex.dev/ns/v0.2.0",
+      "@id": "https://example.org/vex/web-1",
+      "author": "Security Team",
+      "timestamp": "2024-03-01T10:00:00Z",
+      "version": 1,
+      "statements": [
+        {
+          "vulnerability": { "name": "CVE-2024-0727" },
+          "products": [{ "@id": "pkg:deb/debian/openssl" }],
+          "status": "not_affected",
+          "justification": "vulnerable_code_not_in_execute_path"
+        },
+        {
+          "vulnerability": "CVE-2023-44487",
+          "products": [{
+            "@id": "pkg:oci/web",
+            "subcomponents": [{ "@id": "pkg:deb/debian/nginx@1.22.1-9?arch=amd64" }]
+          }],
+          "status": "fixed"
+        },
+        {
+          "vulnerability": { "name": "CVE-2024-2511" },
+          "products": ["pkg:deb/debian/openssl"],
+          "status": "not_affected",
+          "justification": "component_not_present"
+        },
+        {
+          "vulnerability": { "name": "CVE-2024-2511" },
+          "products": ["pkg:deb/debian/openssl"],
+          "status": "affected",
+          "action_statement": "Update",
+          "timestamp": "2024-04-01T10:00:00Z"
+        }
+      ]
+    }"#;
+
+    #[test]
+    fn test_suppresses() {
+        let mut vex = Vex::default();
+        vex.add(parse(TRIAGE).unwrap());
+        assert_eq!(vex.statements.len(), 4);
+
+        let openssl = package_purl("deb", "debian", "openssl", "3.0.11-1~deb12u2", "amd64");
+        assert!(vex.suppresses("CVE-2024-0727", &openssl));
+        assert!(!vex.suppresses("CVE-2024-0727", "pkg:deb/ubuntu/openssl@3.0.2"));
+        assert!(!vex.suppresses("CVE-2024-9999", &openssl));
+        // The later statement says affected
+        assert_eq!(
+            vex.status("CVE-2024-2511", &openssl).unwrap().status,
+            Status::Affected
+        );
+        assert!(!vex.suppresses("CVE-2024-2511", &openssl));
+
+        assert!(vex.suppresses("CVE-2023-44487", "pkg:deb/debian/nginx@1.22.1-9"));
+        assert!(!vex.suppresses("CVE-2023-44487", "pkg:deb/debian/nginx@1.22.1-8"));
+    }
+
+    #[test]
+    fn test_export_round_trip() {
+        let purl = package_purl("deb", "debian", "libstdc++6", "1:12.2.0-14", "");
+        assert_eq!(purl, "pkg:deb/debian/libstdc%2B%2B6@1%3A12.2.0-14");
+
+        let document = VexDocument::new(
+            "guestctl",
+            vec![Statement::affected(
+                "CVE-2024-0001",
+                &purl,
+                "libstdc++6",
+                Some("1:12.2.0-15"),
+            )],
+        );
+        let json = serde_json::to_value(&document).unwrap();
+        assert_eq!(json["@context"], CONTEXT);
+        assert_eq!(
+            json["statements"][0]["vulnerability"]["name"],
+            "CVE-2024-0001"
+        );
+        assert_eq!(json["statements"][0]["status"], "affected");
+        assert_eq!(json["statements"][0]["products"][0]["@id"], purl);
+        assert_eq!(
+            json["statements"][0]["action_statement"],
+            "Update libstdc++6 to 1:12.2.0-15 or later"
+        );
+
+        let parsed = parse(&json.to_string()).unwrap();
+        assert_eq!(parsed.statements[0].products[0].id, purl);
+        assert!(parse(r#"{"@context":"https://example.org","@id":"x","author":"a","timestamp":"t","version":1}"#).is_err());
+    }
+
+    #[test]
+    fn test_purl_parse() {
+        let purl =
+            Purl::parse("pkg:rpm/redhat/openssl-libs@1%3A3.0.7-25.el9_3?arch=x86_64").unwrap();
+        assert_eq!(purl.package_type, "rpm");
+        assert_eq!(purl.namespace.as_deref(), Some("redhat"));
+        assert_eq!(purl.name, "openssl-libs");
+        assert_eq!(purl.version.as_deref(), Some("1:3.0.7-25.el9_3"));
+        assert!(Purl::parse("pkg:generic").is_none());
+        assert!(Purl::parse("https://example.org/product").is_none());
+    }
+}
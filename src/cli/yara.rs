@@ -0,0 +1,435 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! YARA scanning of guest files with yara-x
+//!
+//! Rules come from a `.yar` file or a directory of them; each file gets
+//! its own namespace, named after it, so rule names may repeat across
+//! files. Scanning needs the `yara` feature.
+
+use super::sarif::{self, ReportingDescriptor, SarifResult};
+use anyhow::{Context, Result};
+use serde::Serialize;
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+/// A rule matching a guest file
+#[derive(Debug, Clone, Serialize)]
+pub struct YaraMatch {
+    pub path: String,
+    pub rule: String,
+    pub namespace: String,
+    pub tags: Vec<String>,
+    pub metadata: BTreeMap<String, serde_json::Value>,
+    pub strings: Vec<StringMatch>,
+}
+
+/// Where one of the rule's strings matched
+#[derive(Debug, Clone, Serialize)]
+pub struct StringMatch {
+    pub identifier: String,
+    pub offset: usize,
+    pub length: usize,
+}
+
+impl YaraMatch {
+    /// `namespace.rule`
+    pub fn qualified_rule(&self) -> String {
+        format!("{}.{}", self.namespace, self.rule)
+    }
+
+    /// The rule's `severity` metadata if it is one guestctl uses, HIGH
+    /// otherwise
+    pub fn severity(&self) -> String {
+        match self.metadata.get("severity").and_then(|v| v.as_str()) {
+            Some(s)
+                if ["CRITICAL", "HIGH", "MEDIUM", "LOW"].contains(&s.to_uppercase().as_str()) =>
+            {
+                s.to_uppercase()
+            }
+            _ => "HIGH".to_string(),
+        }
+    }
+
+    /// The rule's `description` metadata, or its name
+    pub fn description(&self) -> String {
+        match self.metadata.get("description").and_then(|v| v.as_str()) {
+            Some(description) => description.to_string(),
+            None => format!("YARA rule {}", self.qualified_rule()),
+        }
+    }
+}
+
+/// Outcome of a YARA scan
+#[derive(Debug, Default, Serialize)]
+pub struct YaraReport {
+    pub files_scanned: usize,
+    /// Files that could not be read or timed out
+    pub errors: usize,
+    pub matches: Vec<YaraMatch>,
+}
+
+/// The rule files at `path`: the file itself, or the `.yar` and `.yara`
+/// files in a directory
+#[cfg_attr(not(feature = "yara"), allow(dead_code))]
+pub fn rule_files(path: &Path) -> Result<Vec<PathBuf>> {
+    if !path.is_dir() {
+        anyhow::ensure!(path.exists(), "YARA rules not found: {}", path.display());
+        return Ok(vec![path.to_path_buf()]);
+    }
+
+    let mut files = Vec::new();
+    for entry in
+        std::fs::read_dir(path).with_context(|| format!("Failed to read {}", path.display()))?
+    {
+        let file = entry?.path();
+        if matches!(
+            file.extension().and_then(|e| e.to_str()),
+            Some("yar" | "yara")
+        ) {
+            files.push(file);
+        }
+    }
+    files.sort();
+    anyhow::ensure!(!files.is_empty(), "No .yar files in {}", path.display());
+    Ok(files)
+}
+
+/// SARIF rules and results for YARA matches
+pub fn sarif_results(matches: &[YaraMatch]) -> (Vec<ReportingDescriptor>, Vec<SarifResult>) {
+    let mut rules: BTreeMap<String, ReportingDescriptor> = BTreeMap::new();
+    let mut results = Vec::new();
+
+    for m in matches {
+        let id = format!("yara/{}", m.qualified_rule());
+        rules.entry(id.clone()).or_insert_with(|| {
+            let mut properties: serde_json::Map<String, serde_json::Value> = m
+                .metadata
+                .iter()
+                .map(|(k, v)| (k.clone(), v.clone()))
+                .collect();
+            if !m.tags.is_empty() {
+                properties.insert("tags".to_string(), m.tags.clone().into());
+            }
+            ReportingDescriptor {
+                id: id.clone(),
+                short_description: Some(sarif::Message {
+                    text: m.description(),
+                }),
+                properties,
+            }
+        });
+
+        let mut result = SarifResult::new(&id, &m.severity(), m.description(), &m.path);
+        if let Some(first) = m.strings.first() {
+            result = result.with_bytes(first.offset, first.length);
+        }
+        results.push(result);
+    }
+
+    (rules.into_values().collect(), results)
+}
+
+#[cfg(feature = "yara")]
+pub use engine::YaraRules;
+
+#[cfg(not(feature = "yara"))]
+pub use disabled::YaraRules;
+
+#[cfg(feature = "yara")]
+mod engine {
+    use super::*;
+    use guestkit::Guestfs;
+    use rayon::prelude::*;
+    use std::time::Duration;
+
+    /// Files larger than this are not scanned
+    const MAX_FILE_SIZE: i64 = 64 * 1024 * 1024;
+
+    /// Bytes read before a batch is scanned
+    const BATCH_BYTES: usize = 128 * 1024 * 1024;
+
+    /// Per-file scan timeout
+    const TIMEOUT: Duration = Duration::from_secs(30);
+
+    /// Compiled rules
+    pub struct YaraRules {
+        rules: yara_x::Rules,
+    }
+
+    impl YaraRules {
+        /// Compile the rules at `path`
+        pub fn load(path: &Path) -> Result<Self> {
+            let mut compiler = yara_x::Compiler::new();
+            for file in rule_files(path)? {
+                let source = std::fs::read_to_string(&file)
+                    .with_context(|| format!("Failed to read {}", file.display()))?;
+                let namespace = file
+                    .file_stem()
+                    .map(|s| s.to_string_lossy().into_owned())
+                    .unwrap_or_default();
+                let origin = file.display().to_string();
+                compiler.new_namespace(&namespace);
+                compiler
+                    .add_source(yara_x::SourceCode::from(source.as_str()).with_origin(&origin))
+                    .with_context(|| format!("Failed to compile {}", file.display()))?;
+            }
+            Ok(Self {
+                rules: compiler.build(),
+            })
+        }
+
+        #[cfg(test)]
+        fn from_source(source: &str) -> Result<Self> {
+            Ok(Self {
+                rules: yara_x::compile(source)?,
+            })
+        }
+
+        pub fn rule_count(&self) -> usize {
+            self.rules.iter().count()
+        }
+
+        /// Rules matching one file's content
+        fn scan(scanner: &mut yara_x::Scanner, path: &str, data: &[u8]) -> Result<Vec<YaraMatch>> {
+            let results = scanner.scan(data)?;
+            Ok(results
+                .matching_rules()
+                .map(|rule| YaraMatch {
+                    path: path.to_string(),
+                    rule: rule.identifier().to_string(),
+                    namespace: rule.namespace().to_string(),
+                    tags: rule.tags().map(|t| t.identifier().to_string()).collect(),
+                    metadata: rule
+                        .metadata()
+                        .map(|(key, value)| (key.to_string(), meta_json(value)))
+                        .collect(),
+                    strings: rule
+                        .patterns()
+                        .flat_map(|pattern| {
+                            let identifier = pattern.identifier().to_string();
+                            pattern
+                                .matches()
+                                .map(|m| StringMatch {
+                                    identifier: identifier.clone(),
+                                    offset: m.range().start,
+                                    length: m.range().len(),
+                                })
+                                .collect::<Vec<_>>()
+                        })
+                        .collect(),
+                })
+                .collect())
+        }
+
+        fn scan_batch(&self, batch: &[(String, Vec<u8>)], report: &mut YaraReport) {
+            let outcomes: Vec<_> = batch
+                .par_iter()
+                .map_init(
+                    || {
+                        let mut scanner = yara_x::Scanner::new(&self.rules);
+                        scanner.set_timeout(TIMEOUT);
+                        scanner
+                    },
+                    |scanner, (path, data)| (path, Self::scan(scanner, path, data)),
+                )
+                .collect();
+
+            for (path, outcome) in outcomes {
+                match outcome {
+                    Ok(matches) => report.matches.extend(matches),
+                    Err(e) => {
+                        log::warn!("YARA scan of {} failed: {}", path, e);
+                        report.errors += 1;
+                    }
+                }
+            }
+        }
+
+        /// Scan the regular files below `bases`
+        ///
+        /// `progress` is called with the number of files scanned and the
+        /// total.
+        pub fn scan_guest(
+            &self,
+            g: &mut Guestfs,
+            bases: &[&str],
+            mut progress: impl FnMut(usize, usize),
+        ) -> YaraReport {
+            let mut report = YaraReport::default();
+
+            let mut files = Vec::new();
+            for base in bases {
+                if g.is_dir(base).unwrap_or(false) {
+                    files.extend(g.find(base).unwrap_or_default());
+                }
+            }
+            files.sort();
+            files.dedup();
+
+            let mut batch = Vec::new();
+            let mut batch_bytes = 0;
+            for (i, file) in files.iter().enumerate() {
+                progress(i + 1, files.len());
+                match g.stat(file) {
+                    Ok(stat) if stat.size <= MAX_FILE_SIZE => {}
+                    _ => continue,
+                }
+                match g.read_file(file) {
+                    Ok(data) => {
+                        batch_bytes += data.len();
+                        batch.push((file.clone(), data));
+                    }
+                    Err(_) => report.errors += 1,
+                }
+                if batch_bytes >= BATCH_BYTES {
+                    self.scan_batch(&batch, &mut report);
+                    report.files_scanned += batch.len();
+                    batch.clear();
+                    batch_bytes = 0;
+                }
+            }
+            self.scan_batch(&batch, &mut report);
+            report.files_scanned += batch.len();
+
+            report
+        }
+    }
+
+    fn meta_json(value: yara_x::MetaValue) -> serde_json::Value {
+        match value {
+            yara_x::MetaValue::Integer(i) => i.into(),
+            yara_x::MetaValue::Float(f) => f.into(),
+            yara_x::MetaValue::Bool(b) => b.into(),
+            yara_x::MetaValue::String(s) => s.into(),
+            yara_x::MetaValue::Bytes(b) => b.to_string().into(),
+        }
+    }
+
+    #[cfg(test)]
+    mod tests {
+        use super::*;
+
+        #[test]
+        fn test_scan() {
+            let rules = YaraRules::from_source(
+                r#"
+                rule Miner : cryptominer {
+                    meta:
+                        description = "XMRig miner"
+                        severity = "critical"
+                    strings:
+                        $pool = "stratum+tcp://"
+                    condition:
+                        $pool
+                }
+                "#,
+            )
+            .unwrap();
+            assert_eq!(rules.rule_count(), 1);
+
+            let mut scanner = yara_x::Scanner::new(&rules.rules);
+            let data = b"url = stratum+tcp://pool.example:3333";
+            let matches = YaraRules::scan(&mut scanner, "/tmp/.x/config.json", data).unwrap();
+            assert_eq!(matches.len(), 1);
+            assert_eq!(matches[0].rule, "Miner");
+            assert_eq!(matches[0].tags, ["cryptominer"]);
+            assert_eq!(matches[0].severity(), "CRITICAL");
+            assert_eq!(matches[0].strings[0].identifier, "$pool");
+            assert_eq!(matches[0].strings[0].offset, 6);
+
+            assert!(YaraRules::scan(&mut scanner, "/etc/hosts", b"127.0.0.1")
+                .unwrap()
+                .is_empty());
+        }
+    }
+}
+
+#[cfg(not(feature = "yara"))]
+mod disabled {
+    use super::*;
+    use guestkit::Guestfs;
+
+    /// Compiled rules; none without the `yara` feature
+    pub enum YaraRules {}
+
+    impl YaraRules {
+        pub fn load(_path: &Path) -> Result<Self> {
+            anyhow::bail!("YARA scanning not enabled. Rebuild with --features yara.");
+        }
+
+        pub fn rule_count(&self) -> usize {
+            match *self {}
+        }
+
+        pub fn scan_guest(
+            &self,
+            _g: &mut Guestfs,
+            _bases: &[&str],
+            _progress: impl FnMut(usize, usize),
+        ) -> YaraReport {
+            match *self {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn yara_match(metadata: &[(&str, &str)]) -> YaraMatch {
+        YaraMatch {
+            path: "/tmp/x".to_string(),
+            rule: "Miner".to_string(),
+            namespace: "miners".to_string(),
+            tags: vec!["cryptominer".to_string()],
+            metadata: metadata
+                .iter()
+                .map(|(k, v)| (k.to_string(), (*v).into()))
+                .collect(),
+            strings: vec![StringMatch {
+                identifier: "$pool".to_string(),
+                offset: 6,
+                length: 14,
+            }],
+        }
+    }
+
+    #[test]
+    fn test_severity() {
+        assert_eq!(yara_match(&[]).severity(), "HIGH");
+        assert_eq!(yara_match(&[("severity", "medium")]).severity(), "MEDIUM");
+        assert_eq!(yara_match(&[("severity", "7")]).severity(), "HIGH");
+        assert_eq!(yara_match(&[]).description(), "YARA rule miners.Miner");
+    }
+
+    #[test]
+    fn test_rule_files() {
+        let dir = tempfile::tempdir().unwrap();
+        for name in ["b.yar", "a.yara", "README.md"] {
+            std::fs::write(dir.path().join(name), "").unwrap();
+        }
+        let files = rule_files(dir.path()).unwrap();
+        let names: Vec<_> = files
+            .iter()
+            .map(|f| f.file_name().unwrap().to_str().unwrap())
+            .collect();
+        assert_eq!(names, ["a.yara", "b.yar"]);
+
+        assert_eq!(rule_files(&files[1]).unwrap(), [files[1].clone()]);
+        assert!(rule_files(&dir.path().join("missing.yar")).is_err());
+    }
+
+    #[test]
+    fn test_sarif_results() {
+        let matches = vec![
+            yara_match(&[("description", "XMRig miner")]),
+            yara_match(&[("description", "XMRig miner")]),
+        ];
+        let (rules, results) = sarif_results(&matches);
+        assert_eq!(rules.len(), 1);
+        assert_eq!(rules[0].id, "yara/miners.Miner");
+        assert_eq!(rules[0].properties["description"], "XMRig miner");
+        assert_eq!(rules[0].properties["tags"][0], "cryptominer");
+        assert_eq!(results.len(), 2);
+        assert_eq!(results[0].level, "error");
+    }
+}
@@ -0,0 +1,657 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Parallel sector copy engine
+//!
+//! Copies guest data into a raw file in 1 MiB chunks. Only the extents of
+//! the source's allocation map are read, and chunks that turn out to be all
+//! zero are left as holes too. File-to-file copies go through io_uring on Linux
+//! with up to `queue_depth` reads and writes in flight; without io_uring
+//! (old kernels, seccomp policies, the `io-uring` feature off) and for
+//! sources mapped in user space (VMDK, VHDX), `jobs` threads pread and
+//! pwrite instead. Either way, reads keep to the bandwidth limit and all
+//! I/O is submitted at the I/O priority of the options, if set.
+
+use super::throttle::{IoPriority, RateLimiter};
+use super::verify::BlockHasher;
+use crate::core::{Error, Result};
+use crate::disk::allocation::{allocated_bytes, Extent};
+use serde::Serialize;
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Mutex};
+
+/// Bytes per read and write, and between progress reports
+pub const CHUNK_SIZE: usize = 1024 * 1024;
+
+/// qemu-img convert runs 8 coroutines unless told otherwise
+pub const DEFAULT_JOBS: usize = 8;
+
+/// io_uring requests in flight by default
+pub const DEFAULT_QUEUE_DEPTH: usize = 32;
+
+/// How much to copy at once, and how fast
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CopyOptions {
+    /// Threads for the thread-pool engine, coroutines for qemu-img
+    pub jobs: usize,
+    /// Chunks in flight with io_uring
+    pub queue_depth: usize,
+    /// Bytes read per second at most, over all threads
+    pub bwlimit: Option<u64>,
+    /// I/O scheduling class of the copy
+    pub io_priority: Option<IoPriority>,
+}
+
+impl Default for CopyOptions {
+    fn default() -> Self {
+        Self {
+            jobs: DEFAULT_JOBS,
+            queue_depth: DEFAULT_QUEUE_DEPTH,
+            bwlimit: None,
+            io_priority: None,
+        }
+    }
+}
+
+impl CopyOptions {
+    /// Shared limiter for the bandwidth limit, if any
+    pub(crate) fn rate_limiter(&self) -> Option<RateLimiter> {
+        self.bwlimit.map(RateLimiter::new)
+    }
+
+    /// Give the calling thread the I/O priority, if any
+    pub(crate) fn apply_io_priority(&self) {
+        if let Some(priority) = self.io_priority {
+            if let Err(e) = priority.apply() {
+                log::warn!("Failed to set I/O priority {:?}: {}", priority, e);
+            }
+        }
+    }
+}
+
+/// Engine that did a copy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum CopyBackend {
+    IoUring,
+    Threads,
+}
+
+/// Outcome of a copy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct CopyStats {
+    pub backend: CopyBackend,
+    /// Bytes read: the allocated extents of the source
+    pub read: u64,
+    /// Bytes actually written (non-zero chunks)
+    pub written: u64,
+}
+
+/// Copy the `extents` of `source` into `dest`
+///
+/// `dest` should be empty or already zeros where the extents are; zero
+/// chunks are skipped, not written. Each chunk read is given to `hasher`,
+/// if any; the extents must then cover whole blocks (see
+/// [`BlockHasher::cover`]). `on_progress` gets the bytes of the extents done
+/// so far and cancels the copy by returning false.
+pub fn copy_file<F>(
+    source: &File,
+    dest: &File,
+    extents: &[Extent],
+    options: &CopyOptions,
+    hasher: Option<&BlockHasher>,
+    mut on_progress: F,
+) -> Result<CopyStats>
+where
+    F: FnMut(u64) -> bool,
+{
+    // Hashing is done by the threads, in parallel
+    #[cfg(all(target_os = "linux", feature = "io-uring"))]
+    if hasher.is_none() {
+        match uring::Ring::new(options.queue_depth) {
+            Ok(mut ring) => return ring.copy(source, dest, extents, options, &mut on_progress),
+            Err(e) => log::debug!("io_uring unavailable ({}), copying with threads", e),
+        }
+    }
+
+    copy_with(
+        |offset, buf| source.read_exact_at(buf, offset).map_err(Error::Io),
+        dest,
+        extents,
+        options,
+        hasher,
+        &mut on_progress,
+    )
+}
+
+/// Copy the `extents` produced by `read_at` into `dest` with `jobs` threads
+///
+/// For sources mapped in user space, such as VMDK grains or VHDX blocks.
+/// Otherwise as [`copy_file`].
+pub fn copy_with<R, F>(
+    read_at: R,
+    dest: &File,
+    extents: &[Extent],
+    options: &CopyOptions,
+    hasher: Option<&BlockHasher>,
+    mut on_progress: F,
+) -> Result<CopyStats>
+where
+    R: Fn(u64, &mut [u8]) -> Result<()> + Sync,
+    F: FnMut(u64) -> bool,
+{
+    let read = allocated_bytes(extents);
+    let chunks = read.div_ceil(CHUNK_SIZE as u64);
+    let jobs = options.jobs.clamp(1, chunks.max(1) as usize);
+    let next = Mutex::new(chunks_of(extents));
+    let limiter = options.rate_limiter();
+    let written = AtomicU64::new(0);
+    let stop = AtomicBool::new(false);
+    let failure = Mutex::new(None);
+    let mut cancelled = false;
+
+    std::thread::scope(|scope| {
+        let (done_tx, done_rx) = mpsc::channel();
+        for _ in 0..jobs {
+            let done_tx = done_tx.clone();
+            let (read_at, next, limiter, written, stop, failure) =
+                (&read_at, &next, &limiter, &written, &stop, &failure);
+            scope.spawn(move || {
+                options.apply_io_priority();
+                let mut buf = vec![0u8; CHUNK_SIZE];
+                while !stop.load(Ordering::Relaxed) {
+                    let Some((offset, len)) = next.lock().unwrap().next() else {
+                        break;
+                    };
+                    if let Some(limiter) = limiter {
+                        limiter.acquire(len as u64);
+                    }
+                    let chunk = &mut buf[..len];
+                    let result = read_at(offset, chunk).and_then(|()| {
+                        if !is_zero(chunk) {
+                            dest.write_all_at(chunk, offset).map_err(Error::Io)?;
+                            written.fetch_add(chunk.len() as u64, Ordering::Relaxed);
+                        }
+                        // Journaled hashers must only see written chunks
+                        match hasher {
+                            Some(hasher) => hasher.record(offset, chunk),
+                            None => Ok(()),
+                        }
+                    });
+                    if let Err(e) = result {
+                        failure.lock().unwrap().get_or_insert(e);
+                        stop.store(true, Ordering::Relaxed);
+                        break;
+                    }
+                    let _ = done_tx.send(chunk.len() as u64);
+                }
+            });
+        }
+        drop(done_tx);
+
+        // Progress is reported here, as the callback needn't be Sync
+        let mut done = 0;
+        for bytes in done_rx {
+            done += bytes;
+            if !cancelled && !on_progress(done) {
+                cancelled = true;
+                stop.store(true, Ordering::Relaxed);
+            }
+        }
+    });
+
+    if let Some(e) = failure.into_inner().unwrap() {
+        return Err(e);
+    }
+    if cancelled {
+        return Err(Error::Conversion("Conversion cancelled".to_string()));
+    }
+    Ok(CopyStats {
+        backend: CopyBackend::Threads,
+        read,
+        written: written.into_inner(),
+    })
+}
+
+/// The extents cut into chunks of at most [`CHUNK_SIZE`], aligned to it
+fn chunks_of(extents: &[Extent]) -> impl Iterator<Item = (u64, usize)> + Send + '_ {
+    extents.iter().flat_map(|extent| {
+        let mut offset = extent.offset;
+        std::iter::from_fn(move || {
+            if offset >= extent.end() {
+                return None;
+            }
+            let boundary = (offset / CHUNK_SIZE as u64 + 1) * CHUNK_SIZE as u64;
+            let len = boundary.min(extent.end()) - offset;
+            let chunk = (offset, len as usize);
+            offset += len;
+            Some(chunk)
+        })
+    })
+}
+
+/// Whether `buf` holds only zero bytes, a word at a time
+fn is_zero(buf: &[u8]) -> bool {
+    // SAFETY: any bit pattern is a valid u128
+    let (head, words, tail) = unsafe { buf.align_to::<u128>() };
+    head.iter().all(|&b| b == 0) && words.iter().all(|&w| w == 0) && tail.iter().all(|&b| b == 0)
+}
+
+#[cfg(all(target_os = "linux", feature = "io-uring"))]
+mod uring {
+    use super::{
+        allocated_bytes, chunks_of, is_zero, CopyBackend, CopyOptions, CopyStats, CHUNK_SIZE,
+    };
+    use crate::converters::throttle::RateLimiter;
+    use crate::core::{Error, Result};
+    use crate::disk::allocation::Extent;
+    use io_uring::{opcode, types, IoUring};
+    use std::fs::File;
+    use std::io;
+    use std::os::unix::io::AsRawFd;
+
+    enum Stage {
+        Read,
+        Write,
+    }
+
+    /// One chunk in flight; its buffer stays put while the kernel uses it
+    struct Slot {
+        buf: Vec<u8>,
+        offset: u64,
+        len: usize,
+        /// Bytes of the current read or write already done
+        done: usize,
+        stage: Stage,
+    }
+
+    pub(super) struct Ring {
+        ring: IoUring,
+        depth: usize,
+        /// I/O priority of every submission
+        ioprio: u16,
+    }
+
+    impl Ring {
+        /// Fails where io_uring is missing or forbidden
+        pub(super) fn new(queue_depth: usize) -> io::Result<Self> {
+            let depth = queue_depth.clamp(1, 4096);
+            Ok(Self {
+                ring: IoUring::new(depth.next_power_of_two() as u32)?,
+                depth,
+                ioprio: 0,
+            })
+        }
+
+        pub(super) fn copy(
+            &mut self,
+            source: &File,
+            dest: &File,
+            extents: &[Extent],
+            options: &CopyOptions,
+            on_progress: &mut dyn FnMut(u64) -> bool,
+        ) -> Result<CopyStats> {
+            let (source_fd, dest_fd) = (source.as_raw_fd(), dest.as_raw_fd());
+            self.ioprio = options.io_priority.map_or(0, |priority| priority.ioprio());
+            let limiter = options.rate_limiter();
+            let mut chunks = chunks_of(extents);
+            let mut slots: Vec<Slot> = chunks
+                .by_ref()
+                .take(self.depth)
+                .map(|(offset, len)| Slot {
+                    buf: vec![0u8; CHUNK_SIZE],
+                    offset,
+                    len,
+                    done: 0,
+                    stage: Stage::Read,
+                })
+                .collect();
+
+            let mut in_flight = 0;
+            let mut done = 0u64;
+            let mut written = 0u64;
+            let mut failure: Option<Error> = None;
+            let mut cancelled = false;
+
+            for (index, slot) in slots.iter_mut().enumerate() {
+                throttle(&limiter, slot.len);
+                self.submit(slot, index, source_fd, dest_fd)?;
+                in_flight += 1;
+            }
+
+            while in_flight > 0 {
+                self.ring.submit_and_wait(1).map_err(Error::Io)?;
+                let completions: Vec<(usize, i32)> = self
+                    .ring
+                    .completion()
+                    .map(|cqe| (cqe.user_data() as usize, cqe.result()))
+                    .collect();
+
+                for (index, result) in completions {
+                    let slot = &mut slots[index];
+                    in_flight -= 1;
+                    if result < 0 {
+                        failure.get_or_insert(Error::Io(io::Error::from_raw_os_error(-result)));
+                        continue;
+                    }
+                    if result == 0 && matches!(slot.stage, Stage::Read) {
+                        failure.get_or_insert(Error::Io(io::Error::new(
+                            io::ErrorKind::UnexpectedEof,
+                            format!(
+                                "Source ends before offset {}",
+                                slot.offset + slot.done as u64
+                            ),
+                        )));
+                        continue;
+                    }
+                    slot.done += result as usize;
+
+                    // Short read or write: go on from where it stopped
+                    let finished = slot.done == slot.len;
+                    if !finished || failure.is_some() || cancelled {
+                        if !finished && failure.is_none() && !cancelled {
+                            self.submit(slot, index, source_fd, dest_fd)?;
+                            in_flight += 1;
+                        }
+                        continue;
+                    }
+
+                    if matches!(slot.stage, Stage::Read) && !is_zero(&slot.buf[..slot.len]) {
+                        slot.stage = Stage::Write;
+                        slot.done = 0;
+                        self.submit(slot, index, source_fd, dest_fd)?;
+                        in_flight += 1;
+                        continue;
+                    }
+                    if matches!(slot.stage, Stage::Write) {
+                        written += slot.len as u64;
+                    }
+
+                    done += slot.len as u64;
+                    if !on_progress(done) {
+                        cancelled = true;
+                        continue;
+                    }
+                    if let Some(chunk) = chunks.next() {
+                        start_chunk(slot, chunk);
+                        throttle(&limiter, slot.len);
+                        self.submit(slot, index, source_fd, dest_fd)?;
+                        in_flight += 1;
+                    }
+                }
+            }
+
+            if let Some(e) = failure {
+                return Err(e);
+            }
+            if cancelled {
+                return Err(Error::Conversion("Conversion cancelled".to_string()));
+            }
+            Ok(CopyStats {
+                backend: CopyBackend::IoUring,
+                read: allocated_bytes(extents),
+                written,
+            })
+        }
+
+        /// Queue the rest of the slot's read or write
+        fn submit(&mut self, slot: &mut Slot, index: usize, source: i32, dest: i32) -> Result<()> {
+            let offset = slot.offset + slot.done as u64;
+            let remaining = (slot.len - slot.done) as u32;
+            let entry = match slot.stage {
+                Stage::Read => opcode::Read::new(
+                    types::Fd(source),
+                    slot.buf[slot.done..].as_mut_ptr(),
+                    remaining,
+                )
+                .offset(offset)
+                .ioprio(self.ioprio)
+                .build(),
+                Stage::Write => {
+                    opcode::Write::new(types::Fd(dest), slot.buf[slot.done..].as_ptr(), remaining)
+                        .offset(offset)
+                        .ioprio(self.ioprio)
+                        .build()
+                }
+            };
+            // SAFETY: the slot's buffer is neither moved nor freed until its
+            // completion is reaped; there is one entry per slot at most and
+            // the queue holds at least as many entries as there are slots
+            unsafe {
+                self.ring
+                    .submission()
+                    .push(&entry.user_data(index as u64))
+                    .map_err(|_| Error::Io(io::Error::other("io_uring submission queue full")))?;
+            }
+            Ok(())
+        }
+    }
+
+    /// Wait for the bandwidth budget of a chunk about to be read
+    fn throttle(limiter: &Option<RateLimiter>, len: usize) {
+        if let Some(limiter) = limiter {
+            limiter.acquire(len as u64);
+        }
+    }
+
+    fn start_chunk(slot: &mut Slot, (offset, len): (u64, usize)) {
+        slot.offset = offset;
+        slot.len = len;
+        slot.done = 0;
+        slot.stage = Stage::Read;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::verify::Manifest;
+    use super::*;
+
+    /// 5.5 MiB with data in chunks 0, 2 and the partial last one
+    fn source(dir: &std::path::Path) -> (File, Vec<u8>) {
+        let mut data = vec![0u8; 5 * CHUNK_SIZE + CHUNK_SIZE / 2];
+        data[..4096].fill(0x11);
+        data[2 * CHUNK_SIZE + 17] = 0x22;
+        let last = data.len() - 1;
+        data[last] = 0x33;
+        let path = dir.join("source.raw");
+        std::fs::write(&path, &data).unwrap();
+        (File::open(&path).unwrap(), data)
+    }
+
+    fn whole(size: u64) -> Vec<Extent> {
+        vec![Extent {
+            offset: 0,
+            length: size,
+        }]
+    }
+
+    fn dest(dir: &std::path::Path, size: u64) -> (std::path::PathBuf, File) {
+        let path = dir.join("dest.raw");
+        let file = File::create(&path).unwrap();
+        file.set_len(size).unwrap();
+        (path, file)
+    }
+
+    #[test]
+    fn test_copy_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let (source, data) = source(dir.path());
+        let (path, dest) = dest(dir.path(), data.len() as u64);
+
+        let options = CopyOptions {
+            jobs: 3,
+            queue_depth: 4,
+            bwlimit: Some(64 * CHUNK_SIZE as u64),
+            io_priority: Some(IoPriority::BestEffort(7)),
+        };
+        let mut reported = 0;
+        let stats = copy_file(
+            &source,
+            &dest,
+            &whole(data.len() as u64),
+            &options,
+            None,
+            |done| {
+                assert!(done > reported);
+                reported = done;
+                true
+            },
+        )
+        .unwrap();
+        assert_eq!(reported, data.len() as u64);
+        assert_eq!(stats.read, data.len() as u64);
+        // Chunks 0, 2 and 5 hold data
+        assert_eq!(stats.written, 2 * CHUNK_SIZE as u64 + CHUNK_SIZE as u64 / 2);
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+    }
+
+    #[test]
+    fn test_copy_with_threads() {
+        let dir = tempfile::tempdir().unwrap();
+        let (_, data) = source(dir.path());
+        let (path, dest) = dest(dir.path(), data.len() as u64);
+
+        let read_at = |offset: u64, buf: &mut [u8]| {
+            let offset = offset as usize;
+            buf.copy_from_slice(&data[offset..offset + buf.len()]);
+            Ok(())
+        };
+        let options = CopyOptions {
+            jobs: 4,
+            ..CopyOptions::default()
+        };
+        let stats = copy_with(
+            read_at,
+            &dest,
+            &whole(data.len() as u64),
+            &options,
+            None,
+            |_| true,
+        )
+        .unwrap();
+        assert_eq!(stats.backend, CopyBackend::Threads);
+        assert_eq!(std::fs::read(&path).unwrap(), data);
+    }
+
+    #[test]
+    fn test_copy_extents_only() {
+        let dir = tempfile::tempdir().unwrap();
+        let (source, data) = source(dir.path());
+        let (path, dest) = dest(dir.path(), data.len() as u64);
+
+        // The last byte is outside the map, so it isn't copied
+        let extents = [
+            Extent {
+                offset: 0,
+                length: 4096,
+            },
+            Extent {
+                offset: 2 * CHUNK_SIZE as u64 - 10,
+                length: 100,
+            },
+        ];
+        assert_eq!(
+            chunks_of(&extents).collect::<Vec<_>>(),
+            vec![
+                (0, 4096),
+                (2 * CHUNK_SIZE as u64 - 10, 10),
+                (2 * CHUNK_SIZE as u64, 90)
+            ]
+        );
+        let stats = copy_file(
+            &source,
+            &dest,
+            &extents,
+            &CopyOptions::default(),
+            None,
+            |_| true,
+        )
+        .unwrap();
+        assert_eq!(stats.read, 4196);
+        assert_eq!(stats.written, 4096 + 90);
+
+        let mut expected = data.clone();
+        let last = expected.len() - 1;
+        expected[last] = 0;
+        assert_eq!(std::fs::read(&path).unwrap(), expected);
+    }
+
+    #[test]
+    fn test_copy_hashes_blocks() {
+        let dir = tempfile::tempdir().unwrap();
+        let (source, data) = source(dir.path());
+        let (path, dest) = dest(dir.path(), data.len() as u64);
+
+        // Only chunk 2 is mapped; the others hash as zeros
+        let hasher = BlockHasher::new(data.len() as u64);
+        let extents = hasher.cover(&[Extent {
+            offset: 2 * CHUNK_SIZE as u64 + 17,
+            length: 1,
+        }]);
+        let stats = copy_file(
+            &source,
+            &dest,
+            &extents,
+            &CopyOptions::default(),
+            Some(&hasher),
+            |_| true,
+        )
+        .unwrap();
+        assert_eq!(stats.backend, CopyBackend::Threads);
+        assert_eq!(stats.read, CHUNK_SIZE as u64);
+
+        let manifest = hasher.finish();
+        let copied = Manifest::of_image(&path, &CopyOptions::default()).unwrap();
+        assert_eq!(manifest, copied);
+        assert_eq!(manifest.blocks.len(), 6);
+    }
+
+    #[test]
+    fn test_copy_cancel_and_error() {
+        let dir = tempfile::tempdir().unwrap();
+        let (source, data) = source(dir.path());
+        let (_, dest) = dest(dir.path(), data.len() as u64);
+        let options = CopyOptions::default();
+
+        let err = copy_file(
+            &source,
+            &dest,
+            &whole(data.len() as u64),
+            &options,
+            None,
+            |_| false,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("cancelled"));
+
+        // Past the end of the source
+        let err = copy_file(
+            &source,
+            &dest,
+            &whole(data.len() as u64 + 10),
+            &options,
+            None,
+            |_| true,
+        );
+        assert!(err.is_err());
+
+        let failing = |offset: u64, _: &mut [u8]| {
+            if offset >= 3 * CHUNK_SIZE as u64 {
+                Err(Error::Conversion("bad grain".to_string()))
+            } else {
+                Ok(())
+            }
+        };
+        let err = copy_with(
+            failing,
+            &dest,
+            &whole(data.len() as u64),
+            &options,
+            None,
+            |_| true,
+        )
+        .unwrap_err();
+        assert!(err.to_string().contains("bad grain"));
+    }
+}
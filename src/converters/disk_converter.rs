@@ -1,15 +1,32 @@
 // SPDX-License-Identifier: LGPL-3.0-or-later
 //! Disk format converter using qemu-img
 
+use super::copy::{self, CopyOptions};
+use super::journal::{ConvertJournal, JournalHeader};
+use super::verify::{BlockHasher, Manifest, VerificationReport};
 use crate::core::{ConversionResult, DiskFormat, Error, Result};
+use crate::disk::allocation::allocated_bytes;
+use crate::disk::vhdx::{self, VhdxImage};
+use crate::disk::DiskReader;
+use serde::Serialize;
 use serde_json::Value;
+use std::ffi::OsString;
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read};
+use std::os::unix::process::CommandExt;
 use std::path::{Path, PathBuf};
-use std::process::Command;
+use std::process::{Command, Stdio};
 use std::time::Instant;
+use tempfile::NamedTempFile;
 
 /// Disk format converter
+#[derive(Debug, Clone)]
 pub struct DiskConverter {
     qemu_img_path: PathBuf,
+    copy_options: CopyOptions,
+    verify: bool,
+    manifest_path: Option<PathBuf>,
+    resume: bool,
 }
 
 impl Default for DiskConverter {
@@ -23,6 +40,10 @@ impl DiskConverter {
     pub fn new() -> Self {
         Self {
             qemu_img_path: PathBuf::from("qemu-img"),
+            copy_options: CopyOptions::default(),
+            verify: false,
+            manifest_path: None,
+            resume: false,
         }
     }
 
@@ -30,9 +51,46 @@ impl DiskConverter {
     pub fn with_qemu_img_path<P: AsRef<Path>>(path: P) -> Self {
         Self {
             qemu_img_path: path.as_ref().to_path_buf(),
+            copy_options: CopyOptions::default(),
+            verify: false,
+            manifest_path: None,
+            resume: false,
         }
     }
 
+    /// Set the parallelism of copies: threads or io_uring queue depth for
+    /// raw output, coroutines for qemu-img; and their bandwidth limit and
+    /// I/O priority, which qemu-img gets too
+    pub fn with_copy_options(mut self, options: CopyOptions) -> Self {
+        self.copy_options = options;
+        self
+    }
+
+    /// Check the output against a SHA-256 manifest of the source once the
+    /// conversion is done; the report ends up in
+    /// [`ConversionResult::verification`]
+    pub fn with_verify(mut self, verify: bool) -> Self {
+        self.verify = verify;
+        self
+    }
+
+    /// Also save the source's block manifest (JSON) when verifying
+    pub fn with_manifest_path<P: AsRef<Path>>(mut self, path: P) -> Self {
+        self.manifest_path = Some(path.as_ref().to_path_buf());
+        self
+    }
+
+    /// Journal the copy next to the output, and continue from an existing
+    /// journal instead of starting over
+    ///
+    /// Only conversions to raw done without qemu-img can be resumed. The
+    /// output and its journal are kept when the conversion fails, and the
+    /// journal removed once it succeeds.
+    pub fn with_resume(mut self, resume: bool) -> Self {
+        self.resume = resume;
+        self
+    }
+
     /// Convert disk image from one format to another
     ///
     /// # Examples
@@ -63,8 +121,36 @@ impl DiskConverter {
         output_path: P,
         output_format: &str,
         compress: bool,
-        _flatten: bool,
+        flatten: bool,
     ) -> Result<ConversionResult> {
+        self.convert_with_progress(
+            source_path,
+            output_path,
+            output_format,
+            compress,
+            flatten,
+            |_| true,
+        )
+    }
+
+    /// Convert disk image, reporting progress as clusters are copied
+    ///
+    /// `on_progress` receives the completed percentage (0.0-100.0) each time
+    /// qemu-img reports progress. Returning `false` stops the conversion and
+    /// removes the partial output.
+    pub fn convert_with_progress<P, F>(
+        &self,
+        source_path: P,
+        output_path: P,
+        output_format: &str,
+        compress: bool,
+        _flatten: bool,
+        mut on_progress: F,
+    ) -> Result<ConversionResult>
+    where
+        P: AsRef<Path>,
+        F: FnMut(f64) -> bool,
+    {
         let source_path = source_path.as_ref();
         let output_path = output_path.as_ref();
         let start = Instant::now();
@@ -73,62 +159,175 @@ impl DiskConverter {
         let source_format = self.detect_format(source_path)?;
         log::info!("Converting {} -> {}", source_format.as_str(), output_format);
 
+        // Rather than finding out after a long conversion
+        if self.verify && !matches!(output_format, "raw" | "qcow2" | "vmdk" | "vhdx") {
+            return Err(Error::Unsupported(format!(
+                "Verifying {} output",
+                output_format
+            )));
+        }
+
+        // Raw, qcow2, VMDK and VHDX guest data is read here, so raw output
+        // needs no qemu-img (OVA payloads, split and snapshot VMDKs included)
+        if output_format == "raw"
+            && matches!(
+                source_format,
+                DiskFormat::Raw | DiskFormat::Qcow2 | DiskFormat::Vmdk | DiskFormat::Vhdx
+            )
+        {
+            let reader = DiskReader::open(source_path)?;
+            if reader.reads_guest_data() {
+                return copy_to_raw(self, &reader, source_path, output_path, start, on_progress);
+            }
+        }
+
+        // qemu-img starts over every time
+        if self.resume {
+            return Err(Error::Unsupported(format!(
+                "Resuming {} to {} conversions: only raw output from raw, qcow2, VMDK or VHDX images",
+                source_format.as_str(),
+                output_format
+            )));
+        }
+
+        // Removed when dropped, once qemu-img is done with it
+        let replayed = match source_format {
+            DiskFormat::Vhdx => vhdx_as_raw(source_path, output_path)?,
+            _ => None,
+        };
+
         // Build qemu-img command
         let mut cmd = Command::new(&self.qemu_img_path);
-        cmd.arg("convert");
+        cmd.arg("convert").arg("-p");
+        cmd.arg("-m")
+            .arg(self.copy_options.jobs.clamp(1, 16).to_string());
+
+        // Out-of-order writes keep the coroutines busy; compressed and
+        // streamOptimized output must be written in order
+        if !compress && self.copy_options.jobs > 1 {
+            cmd.arg("-W");
+        }
+
+        if let Some(bwlimit) = self.copy_options.bwlimit {
+            cmd.arg("-r").arg(bwlimit.to_string());
+        }
+        if let Some(priority) = self.copy_options.io_priority {
+            // SAFETY: ioprio_set is a plain syscall, safe between fork and exec
+            unsafe {
+                cmd.pre_exec(move || priority.apply());
+            }
+        }
+
+        if replayed.is_some() {
+            cmd.arg("-f").arg("raw");
+        }
 
         if compress && output_format == "qcow2" {
             cmd.arg("-c");
         }
 
+        // A compressed VMDK is a streamOptimized one, the layout OVAs carry
+        if compress && output_format == "vmdk" {
+            cmd.arg("-o").arg("subformat=streamOptimized");
+        }
+
         cmd.arg("-O")
             .arg(output_format)
-            .arg(source_path)
-            .arg(output_path);
+            .arg(replayed.as_ref().map_or(source_path, |raw| raw.path()))
+            .arg(output_path)
+            .stdout(Stdio::piped())
+            .stderr(Stdio::piped());
 
         // Execute conversion
         log::debug!("Executing: {:?}", cmd);
-        match cmd.output() {
-            Ok(output) if output.status.success() => {
-                let metadata = std::fs::metadata(output_path).map_err(Error::Io)?;
-                let duration = start.elapsed().as_secs_f64();
-
-                log::info!(
-                    "Conversion complete: {} bytes in {:.2}s",
-                    metadata.len(),
-                    duration
-                );
+        let mut child = cmd.spawn().map_err(|e| {
+            Error::CommandFailed(format!("Failed to execute qemu-img: {}", e))
+        })?;
 
-                Ok(ConversionResult {
-                    source_path: source_path.to_path_buf(),
-                    output_path: output_path.to_path_buf(),
-                    source_format,
-                    output_format: DiskFormat::from_str(output_format),
-                    output_size: metadata.len(),
-                    duration_secs: duration,
-                    success: true,
-                    error: None,
-                })
+        // Drain stderr on a separate thread so a chatty qemu-img cannot block
+        let stderr = child.stderr.take();
+        let stderr_reader = std::thread::spawn(move || {
+            let mut buf = String::new();
+            if let Some(mut stderr) = stderr {
+                let _ = stderr.read_to_string(&mut buf);
             }
-            Ok(output) => {
-                let error_msg = String::from_utf8_lossy(&output.stderr).to_string();
-                log::error!("Conversion failed: {}", error_msg);
-
-                Ok(ConversionResult {
-                    source_path: source_path.to_path_buf(),
-                    output_path: output_path.to_path_buf(),
-                    source_format,
-                    output_format: DiskFormat::from_str(output_format),
-                    output_size: 0,
-                    duration_secs: start.elapsed().as_secs_f64(),
-                    success: false,
-                    error: Some(error_msg),
-                })
+            buf
+        });
+
+        // qemu-img -p rewrites a "(NN.NN/100%)" line using carriage returns
+        let mut cancelled = false;
+        if let Some(stdout) = child.stdout.take() {
+            let mut reader = BufReader::new(stdout);
+            let mut chunk = Vec::new();
+            while reader.read_until(b'\r', &mut chunk).map_err(Error::Io)? > 0 {
+                if let Some(percent) = parse_progress(&String::from_utf8_lossy(&chunk)) {
+                    if !on_progress(percent) {
+                        cancelled = true;
+                        let _ = child.kill();
+                        break;
+                    }
+                }
+                chunk.clear();
             }
-            Err(e) => Err(Error::CommandFailed(format!(
-                "Failed to execute qemu-img: {}",
-                e
-            ))),
+        }
+
+        let status = child.wait().map_err(Error::Io)?;
+        let stderr = stderr_reader.join().unwrap_or_default();
+
+        if cancelled {
+            let _ = std::fs::remove_file(output_path);
+            return Err(Error::Conversion("Conversion cancelled".to_string()));
+        }
+
+        if status.success() {
+            let metadata = std::fs::metadata(output_path).map_err(Error::Io)?;
+            let duration = start.elapsed().as_secs_f64();
+
+            log::info!(
+                "Conversion complete: {} bytes in {:.2}s",
+                metadata.len(),
+                duration
+            );
+
+            // qemu-img read the source, so read it once more for the manifest
+            let verification = if self.verify {
+                let source = replayed.as_ref().map_or(source_path, |raw| raw.path());
+                let manifest = Manifest::of_image(source, &self.copy_options)?;
+                Some(verify_output(
+                    &manifest,
+                    output_path,
+                    self.manifest_path.as_deref(),
+                    &self.copy_options,
+                )?)
+            } else {
+                None
+            };
+
+            Ok(ConversionResult {
+                source_path: source_path.to_path_buf(),
+                output_path: output_path.to_path_buf(),
+                source_format,
+                output_format: DiskFormat::from_str(output_format),
+                output_size: metadata.len(),
+                duration_secs: duration,
+                success: true,
+                error: None,
+                verification,
+            })
+        } else {
+            log::error!("Conversion failed: {}", stderr);
+
+            Ok(ConversionResult {
+                source_path: source_path.to_path_buf(),
+                output_path: output_path.to_path_buf(),
+                source_format,
+                output_format: DiskFormat::from_str(output_format),
+                output_size: 0,
+                duration_secs: start.elapsed().as_secs_f64(),
+                success: false,
+                error: Some(stderr),
+                verification: None,
+            })
         }
     }
 
@@ -136,12 +335,29 @@ impl DiskConverter {
     pub fn detect_format<P: AsRef<Path>>(&self, image_path: P) -> Result<DiskFormat> {
         let image_path = image_path.as_ref();
 
-        let output = Command::new(&self.qemu_img_path)
+        // qemu-img can't open a VHDX whose log needs replaying
+        if vhdx::is_vhdx(image_path) {
+            return Ok(DiskFormat::Vhdx);
+        }
+
+        let output = match Command::new(&self.qemu_img_path)
             .arg("info")
             .arg("--output=json")
             .arg(image_path)
             .output()
-            .map_err(|e| Error::CommandFailed(format!("Failed to run qemu-img info: {}", e)))?;
+        {
+            Ok(output) => output,
+            // Without qemu-img, go by the magic bytes
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                return Ok(*DiskReader::open(image_path)?.format());
+            }
+            Err(e) => {
+                return Err(Error::CommandFailed(format!(
+                    "Failed to run qemu-img info: {}",
+                    e
+                )))
+            }
+        };
 
         if !output.status.success() {
             return Err(Error::Detection(
@@ -180,6 +396,354 @@ impl DiskConverter {
         serde_json::from_slice(&output.stdout)
             .map_err(|e| Error::InvalidFormat(format!("Failed to parse qemu-img output: {}", e)))
     }
+
+    /// List an image and every image below it in its backing chain
+    ///
+    /// The first entry is `image_path` itself, the last one has no backing
+    /// file.
+    pub fn backing_chain<P: AsRef<Path>>(&self, image_path: P) -> Result<Vec<BackingImage>> {
+        let image_path = image_path.as_ref();
+
+        let output = Command::new(&self.qemu_img_path)
+            .arg("info")
+            .arg("--backing-chain")
+            .arg("--output=json")
+            .arg(image_path)
+            .output()
+            .map_err(|e| Error::CommandFailed(format!("Failed to run qemu-img info: {}", e)))?;
+
+        if !output.status.success() {
+            return Err(Error::Detection(
+                String::from_utf8_lossy(&output.stderr).to_string(),
+            ));
+        }
+
+        let info: Value = serde_json::from_slice(&output.stdout)
+            .map_err(|e| Error::InvalidFormat(format!("Failed to parse qemu-img output: {}", e)))?;
+
+        parse_backing_chain(&info)
+    }
+
+    /// Point an image at a new backing file
+    ///
+    /// In safe mode qemu-img copies every cluster whose content differs
+    /// between the old and the new backing file into the image first, so
+    /// the guest sees the same data afterwards; both backing files must be
+    /// readable. In unsafe mode only the backing file name in the header
+    /// is changed, which is what you want after moving or renaming a
+    /// backing file that still has the same content.
+    ///
+    /// A relative `new_backing` is taken relative to the image's directory.
+    /// `None` removes the backing file, leaving a standalone image.
+    pub fn rebase<P: AsRef<Path>>(
+        &self,
+        image_path: P,
+        new_backing: Option<&Path>,
+        backing_format: Option<&str>,
+        unsafe_mode: bool,
+    ) -> Result<()> {
+        let image_path = image_path.as_ref();
+
+        let args = rebase_args(image_path, new_backing, backing_format, unsafe_mode);
+        log::debug!("Running qemu-img {:?}", args);
+
+        let output = Command::new(&self.qemu_img_path)
+            .args(&args)
+            .output()
+            .map_err(|e| Error::CommandFailed(format!("Failed to run qemu-img rebase: {}", e)))?;
+
+        if !output.status.success() {
+            return Err(Error::CommandFailed(format!(
+                "qemu-img rebase failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        Ok(())
+    }
+}
+
+/// One image in a backing chain, as reported by `qemu-img info`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct BackingImage {
+    pub filename: String,
+    pub format: String,
+    pub virtual_size: u64,
+    /// Bytes allocated on the host; not every protocol reports it
+    pub actual_size: Option<u64>,
+    /// Backing file name as stored in the image header
+    pub backing_filename: Option<String>,
+    pub backing_format: Option<String>,
+}
+
+/// Parse the array printed by `qemu-img info --backing-chain --output=json`
+fn parse_backing_chain(info: &Value) -> Result<Vec<BackingImage>> {
+    // Older qemu-img versions print a single object for an image without
+    // a backing file
+    let entries = match info {
+        Value::Array(entries) => entries.iter().collect::<Vec<_>>(),
+        Value::Object(_) => vec![info],
+        _ => {
+            return Err(Error::InvalidFormat(
+                "Unexpected qemu-img info output".to_string(),
+            ))
+        }
+    };
+
+    entries
+        .into_iter()
+        .map(|entry| {
+            let field = |name: &str| entry.get(name).and_then(|v| v.as_str()).map(String::from);
+
+            Ok(BackingImage {
+                filename: field("filename").ok_or_else(|| {
+                    Error::InvalidFormat("No filename field in qemu-img output".to_string())
+                })?,
+                format: field("format").ok_or_else(|| {
+                    Error::InvalidFormat("No format field in qemu-img output".to_string())
+                })?,
+                virtual_size: entry
+                    .get("virtual-size")
+                    .and_then(|v| v.as_u64())
+                    .unwrap_or(0),
+                actual_size: entry.get("actual-size").and_then(|v| v.as_u64()),
+                backing_filename: field("backing-filename"),
+                backing_format: field("backing-filename-format"),
+            })
+        })
+        .collect()
+}
+
+/// Build the qemu-img arguments for [`DiskConverter::rebase`]
+fn rebase_args(
+    image_path: &Path,
+    new_backing: Option<&Path>,
+    backing_format: Option<&str>,
+    unsafe_mode: bool,
+) -> Vec<OsString> {
+    let mut args: Vec<OsString> = vec!["rebase".into()];
+    if unsafe_mode {
+        args.push("-u".into());
+    }
+    args.push("-b".into());
+    args.push(new_backing.map(OsString::from).unwrap_or_default());
+    if let (Some(_), Some(format)) = (new_backing, backing_format) {
+        args.push("-F".into());
+        args.push(format.into());
+    }
+    args.push(image_path.into());
+    args
+}
+
+/// Write the guest data of `reader` to a sparse raw image
+///
+/// Only the ranges in the source's allocation map are read. Raw sources
+/// are copied with io_uring where the kernel allows it, mapped qcow2, VMDK
+/// and VHDX data by a pool of threads. When verifying or resuming, blocks
+/// are hashed as they are copied: to check the output against them
+/// afterwards, or to journal them.
+fn copy_to_raw<F>(
+    converter: &DiskConverter,
+    reader: &DiskReader,
+    source_path: &Path,
+    output_path: &Path,
+    start: Instant,
+    mut on_progress: F,
+) -> Result<ConversionResult>
+where
+    F: FnMut(f64) -> bool,
+{
+    let options = &converter.copy_options;
+    let size = reader.size();
+    let hasher = (converter.verify || converter.resume).then(|| BlockHasher::new(size));
+    let extents = match &hasher {
+        // Hash whole blocks, not just their allocated parts
+        Some(hasher) => hasher.cover(&reader.allocation()?),
+        None => reader.allocation()?,
+    };
+    let allocated = allocated_bytes(&extents);
+    log::debug!(
+        "{}: {} of {} bytes allocated in {} extents",
+        source_path.display(),
+        allocated,
+        size,
+        extents.len()
+    );
+    let create = || -> Result<File> {
+        let output = File::create(output_path).map_err(Error::Io)?;
+        output.set_len(size).map_err(Error::Io)?;
+        Ok(output)
+    };
+
+    // Pick up where an interrupted run stopped, or journal this one
+    let journal_path = ConvertJournal::path_for(output_path);
+    let (output, hasher) = match hasher {
+        Some(hasher) if converter.resume => {
+            let header = JournalHeader::new(source_path, size)?;
+            match ConvertJournal::resume(&journal_path, &header, output_path, &hasher, options)? {
+                Some(resumed) => {
+                    log::info!(
+                        "{}: resuming, {} bytes already copied",
+                        output_path.display(),
+                        resumed.verified
+                    );
+                    (resumed.output, Some(hasher.with_journal(resumed.journal)))
+                }
+                None => {
+                    let output = create()?;
+                    let journal = ConvertJournal::create(&journal_path, &header)?;
+                    (output, Some(hasher.with_journal(journal)))
+                }
+            }
+        }
+        hasher => (create()?, hasher),
+    };
+    let pending = match &hasher {
+        Some(hasher) => hasher.pending(&extents),
+        None => extents,
+    };
+    let resumed = allocated - allocated_bytes(&pending);
+
+    let progress = |done: u64| on_progress((resumed + done) as f64 * 100.0 / allocated as f64);
+    let copied = match reader.raw_file() {
+        Some(source) => copy::copy_file(
+            source,
+            &output,
+            &pending,
+            options,
+            hasher.as_ref(),
+            progress,
+        ),
+        None => copy::copy_with(
+            |offset, buf| reader.pread_exact(offset, buf),
+            &output,
+            &pending,
+            options,
+            hasher.as_ref(),
+            progress,
+        ),
+    };
+    let stats = match copied.and_then(|stats| output.sync_all().map_err(Error::Io).map(|()| stats))
+    {
+        Ok(stats) => stats,
+        Err(e) => {
+            drop(output);
+            if converter.resume {
+                log::info!(
+                    "{}: kept with its journal, to resume",
+                    output_path.display()
+                );
+            } else {
+                let _ = std::fs::remove_file(output_path);
+            }
+            return Err(e);
+        }
+    };
+
+    log::info!(
+        "Converted {} to raw without qemu-img: {} bytes, {} read, {} written ({:?})",
+        source_path.display(),
+        size,
+        stats.read,
+        stats.written,
+        stats.backend
+    );
+    let manifest = hasher.map(BlockHasher::finish);
+    if converter.resume {
+        let _ = std::fs::remove_file(&journal_path);
+    }
+    let verification = match manifest {
+        Some(manifest) if converter.verify => Some(verify_output(
+            &manifest,
+            output_path,
+            converter.manifest_path.as_deref(),
+            options,
+        )?),
+        _ => None,
+    };
+    Ok(ConversionResult {
+        source_path: source_path.to_path_buf(),
+        output_path: output_path.to_path_buf(),
+        source_format: *reader.format(),
+        output_format: DiskFormat::Raw,
+        output_size: size,
+        duration_secs: start.elapsed().as_secs_f64(),
+        success: true,
+        error: None,
+        verification,
+    })
+}
+
+/// Read back `output_path` and compare it with the source's manifest,
+/// saving the manifest to `manifest_path` first
+fn verify_output(
+    manifest: &Manifest,
+    output_path: &Path,
+    manifest_path: Option<&Path>,
+    options: &CopyOptions,
+) -> Result<VerificationReport> {
+    if let Some(path) = manifest_path {
+        manifest.save(path)?;
+    }
+    let report = manifest.verify(output_path, options)?;
+    if report.verified {
+        log::info!(
+            "{}: {} blocks verified, sha256 {}",
+            output_path.display(),
+            report.blocks,
+            report.output_digest
+        );
+    } else {
+        log::warn!(
+            "{}: verification failed, {} of {} blocks differ",
+            output_path.display(),
+            report.mismatches.len(),
+            report.blocks
+        );
+    }
+    Ok(report)
+}
+
+/// Guest data of a VHDX qemu-img can't read, as a sparse raw file next to
+/// the output
+///
+/// qemu-img refuses to open a VHDX read-only while its log holds updates
+/// (after Hyper-V crashed or the VM was exported while running) and
+/// doesn't read differencing VHDX at all.
+fn vhdx_as_raw(source_path: &Path, output_path: &Path) -> Result<Option<NamedTempFile>> {
+    let image = VhdxImage::open(source_path)?;
+    if !image.log_replayed && !image.is_differencing() {
+        return Ok(None);
+    }
+
+    log::info!(
+        "{}: {}, reading it without qemu-img",
+        source_path.display(),
+        if image.log_replayed {
+            "log replayed"
+        } else {
+            "differencing disk"
+        }
+    );
+    let dir = output_path
+        .parent()
+        .filter(|dir| !dir.as_os_str().is_empty())
+        .unwrap_or_else(|| Path::new("."));
+    let raw = tempfile::Builder::new()
+        .prefix(".guestkit-vhdx-")
+        .suffix(".raw")
+        .tempfile_in(dir)
+        .map_err(Error::Io)?;
+    image.write_raw(raw.path())?;
+    Ok(Some(raw))
+}
+
+/// Parse a qemu-img progress line such as `    (42.50/100%)`
+fn parse_progress(line: &str) -> Option<f64> {
+    let start = line.rfind('(')?;
+    let rest = &line[start + 1..];
+    let end = rest.find('/')?;
+    rest[..end].trim().parse::<f64>().ok()
 }
 
 #[cfg(test)]
@@ -195,6 +759,143 @@ mod tests {
         assert_eq!(DiskFormat::from_str("invalid"), DiskFormat::Unknown);
     }
 
+    #[test]
+    fn test_parse_progress() {
+        assert_eq!(parse_progress("    (0.00/100%)\r"), Some(0.0));
+        assert_eq!(parse_progress("\n    (42.50/100%)\r"), Some(42.5));
+        assert_eq!(parse_progress("    (100.00/100%)\n"), Some(100.0));
+        assert_eq!(parse_progress("qemu-img: error"), None);
+    }
+
+    #[test]
+    fn test_parse_backing_chain() {
+        let info = serde_json::json!([
+            {
+                "filename": "overlay.qcow2",
+                "format": "qcow2",
+                "virtual-size": 10737418240u64,
+                "actual-size": 200704,
+                "backing-filename": "base.qcow2",
+                "full-backing-filename": "/images/base.qcow2",
+                "backing-filename-format": "qcow2"
+            },
+            {
+                "filename": "/images/base.qcow2",
+                "format": "qcow2",
+                "virtual-size": 10737418240u64,
+                "actual-size": 1503232
+            }
+        ]);
+
+        let chain = parse_backing_chain(&info).unwrap();
+        assert_eq!(chain.len(), 2);
+        assert_eq!(chain[0].backing_filename.as_deref(), Some("base.qcow2"));
+        assert_eq!(chain[0].backing_format.as_deref(), Some("qcow2"));
+        assert_eq!(chain[0].actual_size, Some(200704));
+        assert_eq!(chain[1].filename, "/images/base.qcow2");
+        assert_eq!(chain[1].backing_filename, None);
+
+        let single = serde_json::json!({"filename": "a.raw", "format": "raw", "virtual-size": 512});
+        assert_eq!(parse_backing_chain(&single).unwrap()[0].actual_size, None);
+        assert!(parse_backing_chain(&serde_json::json!("oops")).is_err());
+    }
+
+    #[test]
+    fn test_rebase_args() {
+        let image = Path::new("overlay.qcow2");
+
+        let args = rebase_args(image, Some(Path::new("new.qcow2")), Some("qcow2"), false);
+        assert_eq!(
+            args,
+            ["rebase", "-b", "new.qcow2", "-F", "qcow2", "overlay.qcow2"]
+        );
+
+        let args = rebase_args(image, Some(Path::new("moved.raw")), None, true);
+        assert_eq!(args, ["rebase", "-u", "-b", "moved.raw", "overlay.qcow2"]);
+
+        let args = rebase_args(image, None, Some("qcow2"), false);
+        assert_eq!(args, ["rebase", "-b", "", "overlay.qcow2"]);
+    }
+
+    #[test]
+    fn test_vmdk_to_raw_without_qemu_img() {
+        let dir = tempfile::tempdir().unwrap();
+        let mut flat = vec![0u8; 2 * 1024 * 1024];
+        flat[512..516].copy_from_slice(b"data");
+        std::fs::write(dir.path().join("disk-flat.vmdk"), &flat).unwrap();
+        let source = dir.path().join("disk.vmdk");
+        std::fs::write(
+            &source,
+            "# Disk DescriptorFile\nversion=1\ncreateType=\"monolithicFlat\"\n\
+             RW 4096 FLAT \"disk-flat.vmdk\" 0\n",
+        )
+        .unwrap();
+
+        let converter = DiskConverter::with_qemu_img_path("/nonexistent/qemu-img");
+        let output = dir.path().join("disk.raw");
+        let mut reports = 0;
+        let result = converter
+            .convert_with_progress(&source, &output, "raw", false, false, |_| {
+                reports += 1;
+                true
+            })
+            .unwrap();
+
+        assert!(result.success);
+        assert_eq!(result.source_format, DiskFormat::Vmdk);
+        assert_eq!(reports, 2);
+        assert_eq!(std::fs::read(&output).unwrap(), flat);
+        assert!(result.verification.is_none());
+
+        let result = converter
+            .with_verify(true)
+            .convert(&source, &output, "raw", false, false)
+            .unwrap();
+        let report = result.verification.unwrap();
+        assert!(report.verified);
+        assert_eq!(report.blocks, 2);
+    }
+
+    #[test]
+    fn test_resume_without_qemu_img() {
+        let dir = tempfile::tempdir().unwrap();
+        let source = dir.path().join("disk.img");
+        let data: Vec<u8> = (0..3 * 1024 * 1024).map(|i| (i / 512) as u8 | 1).collect();
+        std::fs::write(&source, &data).unwrap();
+        let output = dir.path().join("disk.raw");
+        let journal = ConvertJournal::path_for(&output);
+
+        // Cancelled after the first chunk: output and journal are kept
+        let converter = DiskConverter::with_qemu_img_path("/nonexistent/qemu-img")
+            .with_copy_options(CopyOptions {
+                jobs: 1,
+                ..CopyOptions::default()
+            })
+            .with_resume(true);
+        let cancelled =
+            converter.convert_with_progress(&source, &output, "raw", false, false, |_| false);
+        assert!(cancelled.is_err());
+        assert!(output.exists() && journal.exists());
+
+        let mut first = None;
+        let result = converter
+            .convert_with_progress(&source, &output, "raw", false, false, |percent| {
+                first.get_or_insert(percent);
+                true
+            })
+            .unwrap();
+        assert!(result.success);
+        // Progress counts what the first run copied
+        assert!(first.is_none_or(|percent| percent > 100.0 / 3.0));
+        assert_eq!(std::fs::read(&output).unwrap(), data);
+        assert!(!journal.exists());
+
+        let qcow2 = dir.path().join("disk.qcow2");
+        assert!(converter
+            .convert(&source, &qcow2, "qcow2", false, false)
+            .is_err());
+    }
+
     #[test]
     fn test_disk_format_as_str() {
         assert_eq!(DiskFormat::Qcow2.as_str(), "qcow2");
@@ -0,0 +1,324 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Progress journal of a resumable conversion
+//!
+//! The journal sits next to the output as `OUTPUT.journal`. It is a JSON
+//! Lines file: a header describing the source, then the offset, length and
+//! SHA-256 of each block once it has been written to the output. Resuming
+//! reads the journal back, checks those blocks of the output against their
+//! digests and copies only the blocks that are missing or don't match.
+//!
+//! Records are not synced one by one: a block lost with the page cache
+//! fails its check when resuming and is copied again.
+
+use super::copy::CopyOptions;
+use super::verify::{hash_blocks, BlockHasher, BLOCK_SIZE};
+use crate::core::{Error, Result};
+use crate::disk::allocation::Extent;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::UNIX_EPOCH;
+
+/// What the journaled copy reads; a journal only resumes the same copy
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalHeader {
+    pub source: PathBuf,
+    /// Guest disk size in bytes
+    pub size: u64,
+    /// Source modification time, nanoseconds since the epoch
+    pub modified: Option<u64>,
+    pub block_size: u64,
+}
+
+/// A block written to the output
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct JournalBlock {
+    pub offset: u64,
+    pub length: u64,
+    pub sha256: String,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+enum JournalLine {
+    Header(JournalHeader),
+    Block(JournalBlock),
+}
+
+impl JournalHeader {
+    /// Header for copying `size` bytes of guest data out of `source`
+    pub fn new(source: &Path, size: u64) -> Result<Self> {
+        let metadata = std::fs::metadata(source).map_err(Error::Io)?;
+        Ok(Self {
+            source: std::fs::canonicalize(source).map_err(Error::Io)?,
+            size,
+            modified: metadata
+                .modified()
+                .ok()
+                .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+                .and_then(|since| u64::try_from(since.as_nanos()).ok()),
+            block_size: BLOCK_SIZE,
+        })
+    }
+}
+
+/// Journal of the blocks written to an output, shared by the copy threads
+pub struct ConvertJournal {
+    path: PathBuf,
+    file: Mutex<File>,
+}
+
+/// Output and journal of a copy to continue
+pub struct Resumed {
+    pub output: File,
+    pub journal: ConvertJournal,
+    /// Bytes of the output found intact
+    pub verified: u64,
+}
+
+impl ConvertJournal {
+    /// Journal location for `output`
+    pub fn path_for(output: &Path) -> PathBuf {
+        let mut name = output.as_os_str().to_os_string();
+        name.push(".journal");
+        PathBuf::from(name)
+    }
+
+    /// Start a journal, replacing any previous one
+    pub fn create(path: &Path, header: &JournalHeader) -> Result<Self> {
+        let file = File::create(path).map_err(Error::Io)?;
+        let journal = Self {
+            path: path.to_path_buf(),
+            file: Mutex::new(file),
+        };
+        journal.write_line(&JournalLine::Header(header.clone()))?;
+        Ok(journal)
+    }
+
+    /// Continue the copy into `output_path` journaled at `path`
+    ///
+    /// Blocks of the output matching their journaled digest are restored
+    /// into `hasher`; the others are zeroed, to be copied again. Returns
+    /// `None`, and changes nothing, when there is no journal or output, or
+    /// the journal is of a different copy.
+    pub fn resume(
+        path: &Path,
+        header: &JournalHeader,
+        output_path: &Path,
+        hasher: &BlockHasher,
+        options: &CopyOptions,
+    ) -> Result<Option<Resumed>> {
+        let (found, blocks, valid_len) = match File::open(path) {
+            Ok(file) => Self::read(file)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Error::Io(e)),
+        };
+        if found.as_ref() != Some(header) {
+            log::warn!(
+                "{} is not a journal of this conversion, starting over",
+                path.display()
+            );
+            return Ok(None);
+        }
+        let output = match OpenOptions::new().read(true).write(true).open(output_path) {
+            Ok(output) => output,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(Error::Io(e)),
+        };
+        output.set_len(header.size).map_err(Error::Io)?;
+
+        // Read the journaled blocks back
+        let extents: Vec<Extent> = blocks
+            .iter()
+            .map(|block| Extent {
+                offset: block.offset,
+                length: block.length,
+            })
+            .collect();
+        let found = BlockHasher::new(header.size);
+        hash_blocks(
+            |offset, buf| output.read_exact_at(buf, offset).map_err(Error::Io),
+            &extents,
+            &found,
+            options,
+        )?;
+
+        let mut verified = 0;
+        for block in &blocks {
+            if found.digest(block.offset).as_ref() == Some(&block.sha256) {
+                hasher.restore(block.offset, block.sha256.clone());
+                verified += block.length;
+            } else {
+                log::debug!(
+                    "{}: block at {} changed",
+                    output_path.display(),
+                    block.offset
+                );
+                output
+                    .write_all_at(&vec![0u8; block.length as usize], block.offset)
+                    .map_err(Error::Io)?;
+            }
+        }
+
+        // Drop a record torn by the crash, then carry on appending
+        let file = OpenOptions::new()
+            .write(true)
+            .open(path)
+            .map_err(Error::Io)?;
+        file.set_len(valid_len).map_err(Error::Io)?;
+        let file = OpenOptions::new()
+            .append(true)
+            .open(path)
+            .map_err(Error::Io)?;
+        Ok(Some(Resumed {
+            output,
+            journal: Self {
+                path: path.to_path_buf(),
+                file: Mutex::new(file),
+            },
+            verified,
+        }))
+    }
+
+    /// Header, blocks with their latest record each, and the length of the
+    /// intact lines
+    fn read(file: File) -> Result<(Option<JournalHeader>, Vec<JournalBlock>, u64)> {
+        let file_len = file.metadata().map_err(Error::Io)?.len();
+        let mut header = None;
+        let mut blocks = BTreeMap::new();
+        let mut valid_len = 0u64;
+        for line in BufReader::new(file).split(b'\n') {
+            let line = line.map_err(Error::Io)?;
+            let line_len = line.len() as u64 + 1;
+            // Only the last line can be torn: unterminated or cut in the middle
+            let Ok(parsed) = serde_json::from_slice::<JournalLine>(&line) else {
+                break;
+            };
+            if valid_len + line_len > file_len {
+                break;
+            }
+            valid_len += line_len;
+            match parsed {
+                JournalLine::Header(h) if header.is_none() => header = Some(h),
+                JournalLine::Header(_) => return Ok((None, Vec::new(), 0)),
+                JournalLine::Block(block) => {
+                    blocks.insert(block.offset, block);
+                }
+            }
+        }
+        Ok((header, blocks.into_values().collect(), valid_len))
+    }
+
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Record a block once it has been written
+    pub fn append(&self, block: &JournalBlock) -> Result<()> {
+        self.write_line(&JournalLine::Block(block.clone()))
+    }
+
+    fn write_line(&self, line: &JournalLine) -> Result<()> {
+        let mut json = serde_json::to_vec(line).map_err(|e| Error::Conversion(e.to_string()))?;
+        json.push(b'\n');
+
+        let mut file = self.file.lock().unwrap_or_else(|e| e.into_inner());
+        file.write_all(&json).map_err(Error::Io)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::converters::copy::copy_file;
+
+    #[test]
+    fn test_resume_copies_only_missing_blocks() {
+        let dir = tempfile::tempdir().unwrap();
+        let size = 4 * BLOCK_SIZE;
+        let mut data = vec![0u8; size as usize];
+        data.iter_mut()
+            .enumerate()
+            .for_each(|(i, b)| *b = (i / 4096) as u8 | 1);
+        let source_path = dir.path().join("source.raw");
+        std::fs::write(&source_path, &data).unwrap();
+        let source = File::open(&source_path).unwrap();
+        let output_path = dir.path().join("output.raw");
+        let path = ConvertJournal::path_for(&output_path);
+        assert_eq!(path, dir.path().join("output.raw.journal"));
+        let header = JournalHeader::new(&source_path, size).unwrap();
+        let options = CopyOptions::default();
+        let all = [Extent {
+            offset: 0,
+            length: size,
+        }];
+
+        // A first run that got through blocks 0 and 1 only
+        let output = File::create(&output_path).unwrap();
+        output.set_len(size).unwrap();
+        let hasher =
+            BlockHasher::new(size).with_journal(ConvertJournal::create(&path, &header).unwrap());
+        let first = [Extent {
+            offset: 0,
+            length: 2 * BLOCK_SIZE,
+        }];
+        copy_file(&source, &output, &first, &options, Some(&hasher), |_| true).unwrap();
+        drop(hasher);
+        // Block 1 damaged afterwards, and a record torn by the crash
+        output.write_all_at(b"oops", BLOCK_SIZE + 10).unwrap();
+        drop(output);
+        let mut journal = OpenOptions::new().append(true).open(&path).unwrap();
+        journal.write_all(b"{\"type\":\"block\",\"off").unwrap();
+        drop(journal);
+
+        let hasher = BlockHasher::new(size);
+        let resumed = ConvertJournal::resume(&path, &header, &output_path, &hasher, &options)
+            .unwrap()
+            .unwrap();
+        assert_eq!(resumed.verified, BLOCK_SIZE);
+        let pending = hasher.pending(&all);
+        assert_eq!(
+            pending,
+            [Extent {
+                offset: BLOCK_SIZE,
+                length: 3 * BLOCK_SIZE
+            }]
+        );
+
+        let hasher = hasher.with_journal(resumed.journal);
+        let stats = copy_file(
+            &source,
+            &resumed.output,
+            &pending,
+            &options,
+            Some(&hasher),
+            |_| true,
+        )
+        .unwrap();
+        assert_eq!(stats.read, 3 * BLOCK_SIZE);
+        assert_eq!(std::fs::read(&output_path).unwrap(), data);
+
+        // Every block is journaled now, and the torn record is gone
+        let hasher = BlockHasher::new(size);
+        let resumed = ConvertJournal::resume(&path, &header, &output_path, &hasher, &options)
+            .unwrap()
+            .unwrap();
+        assert_eq!(resumed.verified, size);
+        assert!(hasher.pending(&all).is_empty());
+
+        // A journal of another source is ignored
+        let other = JournalHeader {
+            size: size + 1,
+            ..header
+        };
+        assert!(
+            ConvertJournal::resume(&path, &other, &output_path, &hasher, &options)
+                .unwrap()
+                .is_none()
+        );
+    }
+}
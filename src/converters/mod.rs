@@ -1,6 +1,16 @@
 // SPDX-License-Identifier: LGPL-3.0-or-later
 //! Disk format converters
 
+pub mod copy;
 pub mod disk_converter;
+pub mod journal;
+pub mod ova;
+pub mod throttle;
+pub mod verify;
 
-pub use disk_converter::DiskConverter;
+pub use copy::{CopyBackend, CopyOptions, CopyStats};
+pub use disk_converter::{BackingImage, DiskConverter};
+pub use journal::ConvertJournal;
+pub use ova::{OvaArchive, OvaImport, OvfAppliance};
+pub use throttle::IoPriority;
+pub use verify::{Manifest, VerificationReport};
@@ -0,0 +1,893 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! OVA appliance import
+//!
+//! An OVA is a tar archive holding an OVF descriptor, an optional manifest
+//! of checksums and the disks it references, usually streamOptimized VMDKs.
+//! [`OvaArchive`] reads the descriptor into an [`OvfAppliance`] (CPUs,
+//! memory, firmware, disks and NICs) and imports the disks by extracting
+//! and converting them, checking each against the manifest on the way.
+
+use crate::converters::DiskConverter;
+use crate::core::{Error, Result};
+use crate::export::{DomainDisk, DomainNic, DomainSpec};
+use crate::guestfs::DiskBus;
+use flate2::read::GzDecoder;
+use roxmltree::{Document, Node};
+use serde::Serialize;
+use sha2::{Digest, Sha256, Sha512};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+
+/// Descriptor and manifest are small; refuse anything that isn't
+const MAX_TEXT_MEMBER: u64 = 16 * 1024 * 1024;
+
+// CIM ResourceType values used in VirtualHardwareSection items
+const RESOURCE_CPU: &str = "3";
+const RESOURCE_MEMORY: &str = "4";
+const RESOURCE_IDE: &str = "5";
+const RESOURCE_SCSI: &str = "6";
+const RESOURCE_NIC: &str = "10";
+const RESOURCE_DISK: &str = "17";
+const RESOURCE_SATA: &str = "20";
+
+/// Virtual hardware described by an OVF envelope
+#[derive(Debug, Clone, Serialize)]
+pub struct OvfAppliance {
+    pub name: String,
+    pub os: Option<String>,
+    pub cpus: u32,
+    pub memory_mib: u64,
+    /// "bios" or "efi"
+    pub firmware: String,
+    pub disks: Vec<OvfDisk>,
+    pub nics: Vec<OvfNic>,
+}
+
+/// Disk attached to the virtual system
+#[derive(Debug, Clone, Serialize)]
+pub struct OvfDisk {
+    pub id: String,
+    /// Archive member holding the disk, None for a blank disk
+    pub file: Option<String>,
+    /// Member compression, "gzip" when set
+    pub compression: Option<String>,
+    pub capacity: u64,
+    /// Controller type: ide, scsi, sata or nvme
+    pub bus: String,
+}
+
+/// Network adapter
+#[derive(Debug, Clone, Serialize)]
+pub struct OvfNic {
+    pub name: String,
+    /// Adapter type as written in the OVF (E1000, VmxNet3, ...)
+    pub model: Option<String>,
+    /// Network the adapter connects to
+    pub network: Option<String>,
+    pub mac: Option<String>,
+}
+
+/// Disk written by [`OvaArchive::import`]
+#[derive(Debug, Clone, Serialize)]
+pub struct ImportedDisk {
+    pub disk: OvfDisk,
+    pub path: PathBuf,
+    pub format: String,
+    pub size: u64,
+}
+
+/// Result of [`OvaArchive::import`]
+#[derive(Debug, Clone, Serialize)]
+pub struct OvaImport {
+    pub appliance: OvfAppliance,
+    pub disks: Vec<ImportedDisk>,
+    /// Members checked against the manifest, empty when there is none
+    pub verified: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+struct Member {
+    name: String,
+    offset: u64,
+    size: u64,
+}
+
+/// OVA archive opened for import
+pub struct OvaArchive {
+    pub path: PathBuf,
+    members: Vec<Member>,
+    /// Checksums from the manifest: member name -> (algorithm, hex digest)
+    manifest: HashMap<String, (String, String)>,
+    descriptor: String,
+    /// Members checked so far
+    verified: Vec<String>,
+}
+
+impl OvaArchive {
+    /// Open an OVA, reading its descriptor and manifest
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let mut archive = tar::Archive::new(File::open(path).map_err(Error::Io)?);
+        let mut members = Vec::new();
+        for entry in archive.entries().map_err(Error::Io)? {
+            let entry = entry.map_err(|e| {
+                Error::InvalidFormat(format!("{}: not a tar archive: {}", path.display(), e))
+            })?;
+            if !entry.header().entry_type().is_file() {
+                continue;
+            }
+            let name = entry
+                .path()
+                .map_err(Error::Io)?
+                .to_string_lossy()
+                .into_owned();
+            members.push(Member {
+                name: name.trim_start_matches("./").to_string(),
+                offset: entry.raw_file_position(),
+                size: entry.size(),
+            });
+        }
+
+        // The OVF specification puts the descriptor first; some tools don't
+        let ovf = members
+            .iter()
+            .find(|m| m.name.to_ascii_lowercase().ends_with(".ovf"))
+            .cloned()
+            .ok_or_else(|| {
+                Error::InvalidFormat(format!("{}: no OVF descriptor", path.display()))
+            })?;
+        let mut ova = Self {
+            path: path.to_path_buf(),
+            members,
+            manifest: HashMap::new(),
+            descriptor: String::new(),
+            verified: Vec::new(),
+        };
+
+        let mf = ova
+            .members
+            .iter()
+            .find(|m| m.name.to_ascii_lowercase().ends_with(".mf"))
+            .cloned();
+        if let Some(mf) = mf {
+            ova.manifest = parse_manifest(&ova.read_text(&mf)?);
+        }
+        ova.descriptor = ova.read_text(&ovf)?;
+        if ova.verify(&ovf.name, ova.descriptor.as_bytes())? {
+            ova.verified.push(ovf.name);
+        }
+        Ok(ova)
+    }
+
+    /// Whether the archive has a manifest to check members against
+    pub fn has_manifest(&self) -> bool {
+        !self.manifest.is_empty()
+    }
+
+    /// Virtual hardware from the descriptor
+    pub fn appliance(&self) -> Result<OvfAppliance> {
+        OvfAppliance::parse(&self.descriptor)
+    }
+
+    /// Extract and convert every disk of the appliance into `output_dir`
+    ///
+    /// Disks are named `<appliance>-disk<N>.<format>`. The extracted VMDKs
+    /// are removed after conversion unless `keep_extracted` is set.
+    /// `on_progress` gets the disk index and percentage and cancels the
+    /// import by returning false.
+    pub fn import<F>(
+        &self,
+        converter: &DiskConverter,
+        output_dir: &Path,
+        format: &str,
+        keep_extracted: bool,
+        mut on_progress: F,
+    ) -> Result<OvaImport>
+    where
+        F: FnMut(usize, f64) -> bool,
+    {
+        let appliance = self.appliance()?;
+        std::fs::create_dir_all(output_dir).map_err(Error::Io)?;
+        let output_dir = output_dir.canonicalize().map_err(Error::Io)?;
+        let staging = tempfile::Builder::new()
+            .prefix(".ova-")
+            .tempdir_in(&output_dir)
+            .map_err(Error::Io)?;
+
+        let mut verified = self.verified.clone();
+        let mut disks = Vec::new();
+        for (index, disk) in appliance.disks.iter().enumerate() {
+            let Some(file) = &disk.file else {
+                log::warn!(
+                    "{}: disk {} is blank, not imported",
+                    self.path.display(),
+                    disk.id
+                );
+                continue;
+            };
+            let extracted = staging.path().join(file);
+            if self.extract(file, disk.compression.as_deref(), &extracted)? {
+                verified.push(file.clone());
+            }
+
+            let output = output_dir.join(format!(
+                "{}-disk{}.{}",
+                file_stem(&appliance.name),
+                index + 1,
+                format
+            ));
+            let result = converter.convert_with_progress(
+                &extracted,
+                &output,
+                format,
+                false,
+                false,
+                |percent| on_progress(index, percent),
+            )?;
+            if !result.success {
+                return Err(Error::Conversion(format!(
+                    "{}: {}",
+                    file,
+                    result.error.unwrap_or_default().trim()
+                )));
+            }
+
+            if keep_extracted {
+                let kept = output_dir.join(file);
+                if kept.exists() {
+                    log::warn!("{} exists, extracted disk not kept", kept.display());
+                } else {
+                    std::fs::rename(&extracted, &kept).map_err(Error::Io)?;
+                }
+            }
+            disks.push(ImportedDisk {
+                disk: disk.clone(),
+                path: output,
+                format: format.to_string(),
+                size: result.output_size,
+            });
+        }
+
+        Ok(OvaImport {
+            appliance,
+            disks,
+            verified,
+        })
+    }
+
+    fn member(&self, name: &str) -> Result<&Member> {
+        self.members
+            .iter()
+            .find(|m| m.name == name)
+            .ok_or_else(|| Error::NotFound(format!("{}: no member {}", self.path.display(), name)))
+    }
+
+    fn open_member(&self, member: &Member) -> Result<io::Take<File>> {
+        let mut file = File::open(&self.path).map_err(Error::Io)?;
+        file.seek(SeekFrom::Start(member.offset))
+            .map_err(Error::Io)?;
+        Ok(file.take(member.size))
+    }
+
+    fn read_text(&self, member: &Member) -> Result<String> {
+        if member.size > MAX_TEXT_MEMBER {
+            return Err(Error::InvalidFormat(format!(
+                "{}: {} is {} bytes",
+                self.path.display(),
+                member.name,
+                member.size
+            )));
+        }
+        let mut text = String::new();
+        self.open_member(member)?
+            .read_to_string(&mut text)
+            .map_err(|e| Error::InvalidFormat(format!("{}: {}", member.name, e)))?;
+        Ok(text)
+    }
+
+    /// Check `data` against the manifest, returning whether it was listed
+    fn verify(&self, name: &str, data: &[u8]) -> Result<bool> {
+        let Some(mut checksum) = self.checksum_for(name) else {
+            return Ok(false);
+        };
+        checksum.update(data);
+        self.check(name, checksum).map(|_| true)
+    }
+
+    fn checksum_for(&self, name: &str) -> Option<Checksum> {
+        let (algorithm, _) = self.manifest.get(name)?;
+        let checksum = Checksum::new(algorithm);
+        if checksum.is_none() {
+            log::warn!("{}: {} checksum not verified", name, algorithm);
+        }
+        checksum
+    }
+
+    fn check(&self, name: &str, checksum: Checksum) -> Result<()> {
+        let (algorithm, expected) = &self.manifest[name];
+        let actual = checksum.finalize();
+        if !actual.eq_ignore_ascii_case(expected) {
+            return Err(Error::InvalidFormat(format!(
+                "{}: {} mismatch (manifest {}, archive {})",
+                name, algorithm, expected, actual
+            )));
+        }
+        Ok(())
+    }
+
+    /// Extract a member to `dest`, returning whether it was verified
+    fn extract(&self, name: &str, compression: Option<&str>, dest: &Path) -> Result<bool> {
+        if name.contains('/') || name == ".." || name.is_empty() {
+            return Err(Error::InvalidFormat(format!(
+                "{}: disk file {} is not in the archive root",
+                self.path.display(),
+                name
+            )));
+        }
+        let member = self.member(name)?;
+        let mut source = HashingReader {
+            inner: self.open_member(member)?,
+            checksum: self.checksum_for(name),
+        };
+        let mut out = File::create(dest).map_err(Error::Io)?;
+        match compression {
+            None | Some("identity") => {
+                io::copy(&mut source, &mut out).map_err(Error::Io)?;
+            }
+            Some("gzip") => {
+                let mut decoder = GzDecoder::new(&mut source);
+                io::copy(&mut decoder, &mut out).map_err(Error::Io)?;
+                // Trailing bytes past the gzip stream still count
+                io::copy(&mut source, &mut io::sink()).map_err(Error::Io)?;
+            }
+            Some(other) => {
+                return Err(Error::Unsupported(format!(
+                    "{}: {} compression",
+                    name, other
+                )));
+            }
+        }
+
+        match source.checksum {
+            Some(checksum) => self.check(name, checksum).map(|_| true),
+            None => Ok(false),
+        }
+    }
+}
+
+/// Parse `SHA256(file)= digest` lines
+fn parse_manifest(text: &str) -> HashMap<String, (String, String)> {
+    text.lines()
+        .filter_map(|line| {
+            let (algorithm, rest) = line.trim().split_once('(')?;
+            let (name, digest) = rest.split_once(")=")?;
+            Some((
+                name.to_string(),
+                (algorithm.trim().to_uppercase(), digest.trim().to_string()),
+            ))
+        })
+        .collect()
+}
+
+enum Checksum {
+    Sha256(Sha256),
+    Sha512(Sha512),
+}
+
+impl Checksum {
+    /// SHA1 manifests (older VMware exports) are not checked
+    fn new(algorithm: &str) -> Option<Self> {
+        match algorithm {
+            "SHA256" => Some(Checksum::Sha256(Sha256::new())),
+            "SHA512" => Some(Checksum::Sha512(Sha512::new())),
+            _ => None,
+        }
+    }
+
+    fn update(&mut self, data: &[u8]) {
+        match self {
+            Checksum::Sha256(hasher) => hasher.update(data),
+            Checksum::Sha512(hasher) => hasher.update(data),
+        }
+    }
+
+    fn finalize(self) -> String {
+        match self {
+            Checksum::Sha256(hasher) => format!("{:x}", hasher.finalize()),
+            Checksum::Sha512(hasher) => format!("{:x}", hasher.finalize()),
+        }
+    }
+}
+
+struct HashingReader<R> {
+    inner: R,
+    checksum: Option<Checksum>,
+}
+
+impl<R: Read> Read for HashingReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        if let Some(checksum) = &mut self.checksum {
+            checksum.update(&buf[..n]);
+        }
+        Ok(n)
+    }
+}
+
+impl OvfAppliance {
+    /// Parse an OVF descriptor (OVF 1.x and 2.x)
+    ///
+    /// Only the first virtual system of a collection is read.
+    pub fn parse(xml: &str) -> Result<Self> {
+        let doc = Document::parse(xml)
+            .map_err(|e| Error::InvalidFormat(format!("OVF descriptor: {}", e)))?;
+        let envelope = doc.root_element();
+        if envelope.tag_name().name() != "Envelope" {
+            return Err(Error::InvalidFormat(
+                "OVF descriptor: no Envelope element".to_string(),
+            ));
+        }
+
+        // File id -> (href, compression)
+        let mut files = HashMap::new();
+        for file in descendants(envelope, "File") {
+            if attr(file, "chunkSize").is_some() {
+                return Err(Error::Unsupported("chunked OVF disk files".to_string()));
+            }
+            if let (Some(id), Some(href)) = (attr(file, "id"), attr(file, "href")) {
+                files.insert(id, (href.to_string(), attr(file, "compression")));
+            }
+        }
+
+        // Disk id -> (file, capacity)
+        let mut disk_section = Vec::new();
+        for disk in descendants(envelope, "Disk") {
+            let Some(id) = attr(disk, "diskId") else {
+                continue;
+            };
+            let units = attr(disk, "capacityAllocationUnits")
+                .map_or(Some(1), allocation_units)
+                .unwrap_or(1);
+            let capacity = attr(disk, "capacity")
+                .and_then(|c| c.trim().parse::<u64>().ok())
+                .unwrap_or(0)
+                .saturating_mul(units);
+            let file = attr(disk, "fileRef").and_then(|f| files.get(f));
+            disk_section.push((id, file, capacity));
+        }
+
+        let systems: Vec<_> = descendants(envelope, "VirtualSystem").collect();
+        let system = *systems
+            .first()
+            .ok_or_else(|| Error::InvalidFormat("OVF descriptor: no VirtualSystem".to_string()))?;
+        if systems.len() > 1 {
+            log::warn!(
+                "OVF descriptor has {} virtual systems, reading the first",
+                systems.len()
+            );
+        }
+
+        let name = child(system, "Name")
+            .and_then(|n| n.text())
+            .or_else(|| attr(system, "id"))
+            .unwrap_or("appliance")
+            .trim()
+            .to_string();
+        let os = child(system, "OperatingSystemSection").and_then(|section| {
+            child(section, "Description")
+                .and_then(|d| d.text())
+                .or_else(|| attr(section, "osType"))
+                .map(|s| s.trim().to_string())
+        });
+        let firmware = descendants(system, "Config")
+            .find(|config| attr(*config, "key") == Some("firmware"))
+            .and_then(|config| attr(config, "value"))
+            .map_or("bios", |value| if value == "efi" { "efi" } else { "bios" })
+            .to_string();
+
+        let items: Vec<_> = child(system, "VirtualHardwareSection")
+            .into_iter()
+            .flat_map(|section| section.children())
+            .filter(|n| {
+                matches!(
+                    n.tag_name().name(),
+                    "Item" | "StorageItem" | "EthernetPortItem"
+                )
+            })
+            .collect();
+
+        let mut cpus = 1;
+        let mut memory_mib = 0;
+        let mut controllers = HashMap::new();
+        let mut nics = Vec::new();
+        for item in &items {
+            let field = |name| item_field(*item, name);
+            match field("ResourceType").unwrap_or_default() {
+                RESOURCE_CPU => {
+                    cpus = field("VirtualQuantity")
+                        .and_then(|q| q.parse().ok())
+                        .unwrap_or(cpus);
+                }
+                RESOURCE_MEMORY => {
+                    let units = field("AllocationUnits")
+                        .and_then(allocation_units)
+                        .unwrap_or(1 << 20);
+                    let quantity: u64 = field("VirtualQuantity")
+                        .and_then(|q| q.parse().ok())
+                        .unwrap_or(0);
+                    memory_mib = quantity.saturating_mul(units) >> 20;
+                }
+                resource @ (RESOURCE_IDE | RESOURCE_SCSI | RESOURCE_SATA) => {
+                    let subtype = field("ResourceSubType").unwrap_or_default().to_lowercase();
+                    let bus = match resource {
+                        RESOURCE_IDE => "ide",
+                        RESOURCE_SCSI => "scsi",
+                        _ if subtype.contains("nvme") => "nvme",
+                        _ => "sata",
+                    };
+                    if let Some(id) = field("InstanceID") {
+                        controllers.insert(id, bus);
+                    }
+                }
+                RESOURCE_NIC => nics.push(OvfNic {
+                    name: field("ElementName").unwrap_or("ethernet").to_string(),
+                    model: field("ResourceSubType").map(str::to_string),
+                    network: field("Connection").map(str::to_string),
+                    mac: field("Address").map(str::to_string),
+                }),
+                _ => {}
+            }
+        }
+
+        // Attached disks in hardware order, then any the hardware leaves out
+        let mut disks = Vec::new();
+        for item in &items {
+            if item_field(*item, "ResourceType") != Some(RESOURCE_DISK) {
+                continue;
+            }
+            let Some(host) = item_field(*item, "HostResource") else {
+                continue;
+            };
+            let id = host.rsplit('/').next().unwrap_or(host);
+            let bus = item_field(*item, "Parent")
+                .and_then(|parent| controllers.get(parent).copied())
+                .unwrap_or("scsi");
+            if let Some(pos) = disk_section.iter().position(|(disk_id, ..)| *disk_id == id) {
+                let (id, file, capacity) = disk_section.remove(pos);
+                disks.push(ovf_disk(id, file, capacity, bus));
+            }
+        }
+        for (id, file, capacity) in disk_section {
+            disks.push(ovf_disk(id, file, capacity, "scsi"));
+        }
+
+        Ok(Self {
+            name,
+            os,
+            cpus,
+            memory_mib,
+            firmware,
+            disks,
+            nics,
+        })
+    }
+}
+
+fn ovf_disk(id: &str, file: Option<&(String, Option<&str>)>, capacity: u64, bus: &str) -> OvfDisk {
+    OvfDisk {
+        id: id.to_string(),
+        file: file.map(|(href, _)| href.clone()),
+        compression: file.and_then(|(_, c)| c.map(str::to_string)),
+        capacity,
+        bus: bus.to_string(),
+    }
+}
+
+/// Child element by local name, whatever its namespace
+fn child<'a, 'input>(node: Node<'a, 'input>, name: &str) -> Option<Node<'a, 'input>> {
+    node.children().find(|n| n.tag_name().name() == name)
+}
+
+fn descendants<'a, 'input: 'a>(
+    node: Node<'a, 'input>,
+    name: &'a str,
+) -> impl Iterator<Item = Node<'a, 'input>> + 'a {
+    node.descendants()
+        .filter(move |n| n.tag_name().name() == name)
+}
+
+/// Attribute by local name (ovf:href, vmw:osType, ...)
+fn attr<'a>(node: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    node.attributes()
+        .find(|a| a.name() == name)
+        .map(|a| a.value())
+}
+
+fn item_field<'a>(item: Node<'a, '_>, name: &str) -> Option<&'a str> {
+    child(item, name).and_then(|n| n.text()).map(str::trim)
+}
+
+/// Bytes per unit for "byte * 2^30", "byte", "MegaBytes" and the like
+fn allocation_units(units: &str) -> Option<u64> {
+    let units = units.replace(' ', "").to_lowercase();
+    if let Some(exponent) = units.strip_prefix("byte*2^") {
+        return exponent
+            .parse::<u32>()
+            .ok()
+            .and_then(|e| 1u64.checked_shl(e));
+    }
+    match units.as_str() {
+        "byte" | "bytes" => Some(1),
+        "kb" | "kilobytes" => Some(1 << 10),
+        "mb" | "megabytes" => Some(1 << 20),
+        "gb" | "gigabytes" => Some(1 << 30),
+        _ => units.strip_prefix("byte*").and_then(|n| n.parse().ok()),
+    }
+}
+
+/// Appliance name as a file name component
+fn file_stem(name: &str) -> String {
+    let stem: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.') {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    match stem.trim_start_matches('.') {
+        "" => "appliance".to_string(),
+        stem => stem.to_string(),
+    }
+}
+
+impl OvaImport {
+    /// Domain running the imported appliance, for `to_libvirt_xml`
+    ///
+    /// The domain uses a q35 machine, so IDE and NVMe disks are attached
+    /// as SATA and SCSI disks to a virtio-scsi controller. NICs join the
+    /// libvirt network of the same name as their OVF connection; adjust it
+    /// to the host's networks before defining.
+    pub fn domain_spec(&self) -> DomainSpec {
+        let appliance = &self.appliance;
+        let disks = self
+            .disks
+            .iter()
+            .map(|disk| DomainDisk {
+                path: disk.path.clone(),
+                format: disk.format.clone(),
+                bus: match disk.disk.bus.as_str() {
+                    "scsi" => DiskBus::VirtioScsi,
+                    "virtio" => DiskBus::Virtio,
+                    _ => DiskBus::Sata,
+                },
+            })
+            .collect();
+        let nics = appliance
+            .nics
+            .iter()
+            .map(|nic| DomainNic {
+                network: nic.network.clone().unwrap_or_else(|| "default".to_string()),
+                // pcnet and vmxnet3 are emulated by QEMU; anything else gets e1000
+                model: match nic.model.as_deref().map(str::to_lowercase).as_deref() {
+                    Some("e1000e") => "e1000e",
+                    Some("vmxnet3") => "vmxnet3",
+                    Some("pcnet32") | Some("pcnet") => "pcnet",
+                    Some("virtio") => "virtio",
+                    _ => "e1000",
+                }
+                .to_string(),
+                mac: nic.mac.clone(),
+            })
+            .collect();
+
+        DomainSpec {
+            name: appliance.name.clone(),
+            memory_mib: appliance.memory_mib,
+            vcpus: appliance.cpus,
+            arch: "x86_64".to_string(),
+            uefi: appliance.firmware == "efi",
+            // A description ("Microsoft Windows Server 2019") or a VMware
+            // guest id (windows2019srv_64Guest, winNetStandardGuest)
+            localtime: appliance.os.as_deref().is_some_and(|os| {
+                let os = os.to_lowercase();
+                os.starts_with("win") || os.contains("windows")
+            }),
+            disks,
+            nics,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const OVF: &str = r#"<?xml version="1.0" encoding="UTF-8"?>
+<Envelope xmlns="http://schemas.dmtf.org/ovf/envelope/1"
+    xmlns:ovf="http://schemas.dmtf.org/ovf/envelope/1"
+    xmlns:rasd="http://schemas.dmtf.org/wbem/wscim/1/cim-schema/2/CIM_ResourceAllocationSettingData"
+    xmlns:vmw="http://www.vmware.com/schema/ovf">
+  <References>
+    <File ovf:id="file1" ovf:href="web-disk1.vmdk" ovf:size="1024"/>
+  </References>
+  <DiskSection>
+    <Info>Virtual disks</Info>
+    <Disk ovf:diskId="vmdisk1" ovf:fileRef="file1" ovf:capacity="1"
+        ovf:capacityAllocationUnits="byte * 2^20"/>
+    <Disk ovf:diskId="vmdisk2" ovf:capacity="4096"/>
+  </DiskSection>
+  <VirtualSystem ovf:id="web">
+    <Info>A virtual machine</Info>
+    <Name>web &amp; db</Name>
+    <OperatingSystemSection ovf:id="101" vmw:osType="centos8_64Guest">
+      <Info>The operating system</Info>
+    </OperatingSystemSection>
+    <VirtualHardwareSection>
+      <Item>
+        <rasd:ElementName>2 virtual CPU(s)</rasd:ElementName>
+        <rasd:InstanceID>1</rasd:InstanceID>
+        <rasd:ResourceType>3</rasd:ResourceType>
+        <rasd:VirtualQuantity>2</rasd:VirtualQuantity>
+      </Item>
+      <Item>
+        <rasd:AllocationUnits>byte * 2^20</rasd:AllocationUnits>
+        <rasd:InstanceID>2</rasd:InstanceID>
+        <rasd:ResourceType>4</rasd:ResourceType>
+        <rasd:VirtualQuantity>2048</rasd:VirtualQuantity>
+      </Item>
+      <Item>
+        <rasd:InstanceID>3</rasd:InstanceID>
+        <rasd:ResourceSubType>AHCI</rasd:ResourceSubType>
+        <rasd:ResourceType>20</rasd:ResourceType>
+      </Item>
+      <Item>
+        <rasd:HostResource>ovf:/disk/vmdisk1</rasd:HostResource>
+        <rasd:InstanceID>4</rasd:InstanceID>
+        <rasd:Parent>3</rasd:Parent>
+        <rasd:ResourceType>17</rasd:ResourceType>
+      </Item>
+      <Item>
+        <rasd:Address>00:50:56:aa:bb:cc</rasd:Address>
+        <rasd:Connection>VM Network</rasd:Connection>
+        <rasd:ElementName>Network adapter 1</rasd:ElementName>
+        <rasd:InstanceID>5</rasd:InstanceID>
+        <rasd:ResourceSubType>VmxNet3</rasd:ResourceSubType>
+        <rasd:ResourceType>10</rasd:ResourceType>
+      </Item>
+      <vmw:Config ovf:required="false" vmw:key="firmware" vmw:value="efi"/>
+    </VirtualHardwareSection>
+  </VirtualSystem>
+</Envelope>
+"#;
+
+    /// 1 MiB hosted sparse VMDK whose first 4 KiB grain is 0x5a
+    fn sparse_vmdk() -> Vec<u8> {
+        let descriptor = "# Disk DescriptorFile\nversion=1\ncreateType=\"monolithicSparse\"\n\n\
+                          RW 2048 SPARSE \"web-disk1.vmdk\"\n";
+        let mut image = vec![0u8; 16 * 512];
+        image[0..4].copy_from_slice(b"KDMV");
+        image[4..8].copy_from_slice(&1u32.to_le_bytes());
+        image[12..20].copy_from_slice(&2048u64.to_le_bytes());
+        image[20..28].copy_from_slice(&8u64.to_le_bytes());
+        image[28..36].copy_from_slice(&1u64.to_le_bytes());
+        image[36..44].copy_from_slice(&2u64.to_le_bytes());
+        image[44..48].copy_from_slice(&512u32.to_le_bytes());
+        image[56..64].copy_from_slice(&3u64.to_le_bytes());
+        image[512..512 + descriptor.len()].copy_from_slice(descriptor.as_bytes());
+        // Grain directory at sector 3, grain table at 4, grain 0 at 8
+        image[3 * 512..3 * 512 + 4].copy_from_slice(&4u32.to_le_bytes());
+        image[4 * 512..4 * 512 + 4].copy_from_slice(&8u32.to_le_bytes());
+        image[8 * 512..].fill(0x5a);
+        image
+    }
+
+    /// Build an OVA holding the descriptor, a manifest and the disk
+    fn build_ova(dir: &Path, disk_digest: Option<&str>) -> PathBuf {
+        let vmdk = sparse_vmdk();
+        let digest =
+            disk_digest.map_or_else(|| format!("{:x}", Sha256::digest(&vmdk)), str::to_string);
+        let manifest = format!(
+            "SHA256(web.ovf)= {:x}\nSHA256(web-disk1.vmdk)= {}\n",
+            Sha256::digest(OVF.as_bytes()),
+            digest
+        );
+
+        let path = dir.join("web.ova");
+        let mut builder = tar::Builder::new(File::create(&path).unwrap());
+        for (name, data) in [
+            ("web.ovf", OVF.as_bytes()),
+            ("web.mf", manifest.as_bytes()),
+            ("web-disk1.vmdk", &vmdk[..]),
+        ] {
+            let mut header = tar::Header::new_ustar();
+            header.set_size(data.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder.append_data(&mut header, name, data).unwrap();
+        }
+        builder.finish().unwrap();
+        path
+    }
+
+    #[test]
+    fn test_parse_ovf() {
+        let appliance = OvfAppliance::parse(OVF).unwrap();
+        assert_eq!(appliance.name, "web & db");
+        assert_eq!(appliance.os.as_deref(), Some("centos8_64Guest"));
+        assert_eq!(appliance.cpus, 2);
+        assert_eq!(appliance.memory_mib, 2048);
+        assert_eq!(appliance.firmware, "efi");
+
+        assert_eq!(appliance.disks.len(), 2);
+        assert_eq!(appliance.disks[0].file.as_deref(), Some("web-disk1.vmdk"));
+        assert_eq!(appliance.disks[0].capacity, 1 << 20);
+        assert_eq!(appliance.disks[0].bus, "sata");
+        assert_eq!(appliance.disks[1].file, None);
+        assert_eq!(appliance.disks[1].capacity, 4096);
+
+        assert_eq!(appliance.nics.len(), 1);
+        assert_eq!(appliance.nics[0].model.as_deref(), Some("VmxNet3"));
+        assert_eq!(appliance.nics[0].network.as_deref(), Some("VM Network"));
+    }
+
+    #[test]
+    fn test_allocation_units() {
+        assert_eq!(allocation_units("byte * 2^30"), Some(1 << 30));
+        assert_eq!(allocation_units("MegaBytes"), Some(1 << 20));
+        assert_eq!(allocation_units("byte"), Some(1));
+        assert_eq!(allocation_units("furlongs"), None);
+    }
+
+    #[test]
+    fn test_import_ova() {
+        let dir = tempfile::tempdir().unwrap();
+        let ova = OvaArchive::open(build_ova(dir.path(), None)).unwrap();
+        assert!(ova.has_manifest());
+
+        // Raw output is written natively, so no qemu-img is needed
+        let out = dir.path().join("out");
+        let import = ova
+            .import(&DiskConverter::new(), &out, "raw", false, |_, _| true)
+            .unwrap();
+        assert_eq!(import.disks.len(), 1);
+        assert_eq!(import.verified, vec!["web.ovf", "web-disk1.vmdk"]);
+
+        let disk = &import.disks[0];
+        assert_eq!(disk.path.file_name().unwrap(), "web___db-disk1.raw");
+        let data = std::fs::read(&disk.path).unwrap();
+        assert_eq!(data.len(), 1 << 20);
+        assert!(data[..4096].iter().all(|&b| b == 0x5a));
+        assert!(data[4096..].iter().all(|&b| b == 0));
+        // Only the converted disk is left behind
+        assert_eq!(std::fs::read_dir(&out).unwrap().count(), 1);
+
+        let xml = import.domain_spec().to_libvirt_xml();
+        assert!(xml.contains("<name>web &amp; db</name>"));
+        assert!(xml.contains("<memory unit='MiB'>2048</memory>"));
+        assert!(xml.contains("<os firmware='efi'>"));
+        assert!(xml.contains("<target dev='sda' bus='sata'/>"));
+        assert!(xml.contains("<source network='VM Network'/>"));
+        assert!(xml.contains("<model type='vmxnet3'/>"));
+    }
+
+    #[test]
+    fn test_manifest_mismatch() {
+        let dir = tempfile::tempdir().unwrap();
+        let ova = OvaArchive::open(build_ova(dir.path(), Some(&"0".repeat(64)))).unwrap();
+        let err = ova
+            .import(
+                &DiskConverter::new(),
+                &dir.path().join("out"),
+                "raw",
+                false,
+                |_, _| true,
+            )
+            .unwrap_err();
+        assert!(err.to_string().contains("mismatch"));
+    }
+}
@@ -0,0 +1,155 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Bandwidth and I/O priority limits for conversions
+//!
+//! Conversions on a hypervisor share its disks with running guests. A
+//! bandwidth limit caps the bytes read per second, spread over all copy
+//! threads; an I/O priority class, as set by `ionice`, lets the kernel
+//! serve guest I/O first. Priorities only take effect with the BFQ
+//! scheduler (and CFQ on older kernels).
+
+use crate::core::{Error, Result};
+use serde::Serialize;
+use std::str::FromStr;
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+/// `ioprio_set` target: a process, or the calling thread for pid 0
+const IOPRIO_WHO_PROCESS: libc::c_int = 1;
+const IOPRIO_CLASS_SHIFT: u16 = 13;
+const IOPRIO_CLASS_BE: u16 = 2;
+const IOPRIO_CLASS_IDLE: u16 = 3;
+
+/// I/O scheduling class, as with `ionice -c`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum IoPriority {
+    /// Disk time only when nobody else wants it
+    Idle,
+    /// The default class, at a level from 0 (highest) to 7 (lowest)
+    BestEffort(u8),
+}
+
+impl IoPriority {
+    /// Value for `ioprio_set` and io_uring submissions
+    pub fn ioprio(&self) -> u16 {
+        match self {
+            IoPriority::Idle => IOPRIO_CLASS_IDLE << IOPRIO_CLASS_SHIFT,
+            IoPriority::BestEffort(level) => {
+                (IOPRIO_CLASS_BE << IOPRIO_CLASS_SHIFT) | u16::from(*level)
+            }
+        }
+    }
+
+    /// Apply to the calling thread, and the processes it spawns
+    pub fn apply(&self) -> std::io::Result<()> {
+        // SAFETY: ioprio_set takes plain integers and touches no memory
+        let ret = unsafe {
+            libc::syscall(
+                libc::SYS_ioprio_set,
+                IOPRIO_WHO_PROCESS,
+                0,
+                libc::c_int::from(self.ioprio()),
+            )
+        };
+        if ret < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for IoPriority {
+    type Err = Error;
+
+    /// `idle`, `best-effort` or `best-effort:LEVEL`
+    fn from_str(s: &str) -> Result<Self> {
+        let (class, level) = match s.split_once(':') {
+            Some((class, level)) => (class, Some(level)),
+            None => (s, None),
+        };
+        match (class, level) {
+            ("idle", None) => Ok(IoPriority::Idle),
+            ("best-effort" | "be", None) => Ok(IoPriority::BestEffort(4)),
+            ("best-effort" | "be", Some(level)) => match level.parse::<u8>() {
+                Ok(level) if level <= 7 => Ok(IoPriority::BestEffort(level)),
+                _ => Err(Error::InputValidation(format!(
+                    "Invalid best-effort level '{}': expected 0-7",
+                    level
+                ))),
+            },
+            _ => Err(Error::InputValidation(format!(
+                "Invalid I/O priority '{}': expected idle or best-effort[:0-7]",
+                s
+            ))),
+        }
+    }
+}
+
+/// Shared byte budget of the copy threads
+pub struct RateLimiter {
+    bytes_per_sec: u64,
+    /// When the next transfer may start
+    next: Mutex<Option<Instant>>,
+}
+
+impl RateLimiter {
+    pub fn new(bytes_per_sec: u64) -> Self {
+        Self {
+            bytes_per_sec: bytes_per_sec.max(1),
+            next: Mutex::new(None),
+        }
+    }
+
+    /// Wait until `bytes` more fit in the budget
+    pub fn acquire(&self, bytes: u64) {
+        let cost = Duration::from_secs_f64(bytes as f64 / self.bytes_per_sec as f64);
+        let start = {
+            let mut next = self.next.lock().unwrap();
+            let now = Instant::now();
+            let start = next.map_or(now, |next| next.max(now));
+            *next = Some(start + cost);
+            start
+        };
+        let wait = start.saturating_duration_since(Instant::now());
+        if !wait.is_zero() {
+            std::thread::sleep(wait);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_io_priority() {
+        assert_eq!("idle".parse::<IoPriority>().unwrap(), IoPriority::Idle);
+        assert_eq!(
+            "best-effort:7".parse::<IoPriority>().unwrap(),
+            IoPriority::BestEffort(7)
+        );
+        assert_eq!(
+            "be".parse::<IoPriority>().unwrap(),
+            IoPriority::BestEffort(4)
+        );
+        assert!("best-effort:8".parse::<IoPriority>().is_err());
+        assert!("idle:3".parse::<IoPriority>().is_err());
+        assert!("realtime".parse::<IoPriority>().is_err());
+
+        assert_eq!(IoPriority::Idle.ioprio(), 3 << 13);
+        assert_eq!(IoPriority::BestEffort(7).ioprio(), (2 << 13) | 7);
+    }
+
+    #[test]
+    fn test_rate_limiter() {
+        let limiter = RateLimiter::new(10 * 1024 * 1024);
+        let start = Instant::now();
+        // The first megabyte goes at once, the next four take 400 ms
+        for _ in 0..5 {
+            limiter.acquire(1024 * 1024);
+        }
+        let elapsed = start.elapsed();
+        assert!(elapsed >= Duration::from_millis(390), "{:?}", elapsed);
+        assert!(elapsed < Duration::from_secs(2), "{:?}", elapsed);
+    }
+}
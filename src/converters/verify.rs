@@ -0,0 +1,409 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Checksum manifests and conversion verification
+//!
+//! A manifest holds the SHA-256 of every 1 MiB block of guest data, so two
+//! images in different formats can be compared block by block. Converting
+//! to raw without qemu-img hashes the blocks as they are copied; otherwise
+//! the source is read once more after qemu-img is done. Either way, the
+//! output is then read back and checked against the manifest.
+//!
+//! Blocks outside the allocation map of an image are not read: they hash
+//! as zeros.
+
+use super::copy::{CopyOptions, CHUNK_SIZE};
+use super::journal::{ConvertJournal, JournalBlock};
+use crate::core::{Error, Result};
+use crate::disk::allocation::Extent;
+use crate::disk::DiskReader;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::Mutex;
+
+/// Bytes of guest data per manifest entry
+pub const BLOCK_SIZE: u64 = CHUNK_SIZE as u64;
+
+/// SHA-256 of each block of an image's guest data
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Manifest {
+    pub algorithm: String,
+    pub block_size: u64,
+    /// Guest disk size in bytes
+    pub size: u64,
+    /// Hex digest of each block, the last one possibly short
+    pub blocks: Vec<String>,
+    /// Hex digest of the block digests, to compare manifests at a glance
+    pub digest: String,
+}
+
+/// A block whose output digest differs from the manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BlockMismatch {
+    pub index: u64,
+    pub offset: u64,
+    pub expected: String,
+    pub actual: String,
+}
+
+/// Outcome of checking a converted image against its source's manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct VerificationReport {
+    pub output: PathBuf,
+    pub algorithm: String,
+    pub block_size: u64,
+    pub source_size: u64,
+    pub output_size: u64,
+    pub blocks: u64,
+    pub source_digest: String,
+    pub output_digest: String,
+    /// Same size and every block matches
+    pub verified: bool,
+    pub mismatches: Vec<BlockMismatch>,
+}
+
+impl Manifest {
+    /// Hash the guest data of an image
+    ///
+    /// The image must be one whose guest data is read natively: raw,
+    /// qcow2, VMDK or VHDX.
+    pub fn of_image<P: AsRef<Path>>(path: P, options: &CopyOptions) -> Result<Self> {
+        let path = path.as_ref();
+        let reader = DiskReader::open(path)?;
+        if !reader.reads_guest_data() {
+            return Err(Error::Unsupported(format!(
+                "{}: verifying {} images",
+                path.display(),
+                reader.format().as_str()
+            )));
+        }
+
+        let hasher = BlockHasher::new(reader.size());
+        let extents = hasher.cover(&reader.allocation()?);
+        hash_blocks(
+            |offset, buf| reader.pread_exact(offset, buf),
+            &extents,
+            &hasher,
+            options,
+        )?;
+        Ok(hasher.finish())
+    }
+
+    /// Check the guest data of `output` against the manifest
+    pub fn verify<P: AsRef<Path>>(
+        &self,
+        output: P,
+        options: &CopyOptions,
+    ) -> Result<VerificationReport> {
+        let output = output.as_ref();
+        let actual = Self::of_image(output, options)?;
+        let mismatches = self
+            .blocks
+            .iter()
+            .zip(&actual.blocks)
+            .enumerate()
+            .filter(|(_, (expected, actual))| expected != actual)
+            .map(|(index, (expected, actual))| BlockMismatch {
+                index: index as u64,
+                offset: index as u64 * self.block_size,
+                expected: expected.clone(),
+                actual: actual.clone(),
+            })
+            .collect::<Vec<_>>();
+
+        Ok(VerificationReport {
+            output: output.to_path_buf(),
+            algorithm: self.algorithm.clone(),
+            block_size: self.block_size,
+            source_size: self.size,
+            output_size: actual.size,
+            blocks: self.blocks.len() as u64,
+            source_digest: self.digest.clone(),
+            output_digest: actual.digest.clone(),
+            verified: self.size == actual.size && mismatches.is_empty(),
+            mismatches,
+        })
+    }
+
+    pub fn load<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let text = std::fs::read_to_string(path).map_err(Error::Io)?;
+        serde_json::from_str(&text).map_err(|e| Error::InvalidFormat(e.to_string()))
+    }
+
+    pub fn save<P: AsRef<Path>>(&self, path: P) -> Result<()> {
+        let text =
+            serde_json::to_string_pretty(self).map_err(|e| Error::Conversion(e.to_string()))?;
+        std::fs::write(path, text).map_err(Error::Io)
+    }
+}
+
+/// Hash the blocks of `extents` produced by `read_at` with `jobs` threads
+///
+/// The extents must cover whole blocks (see [`BlockHasher::cover`]). Reads
+/// keep to the limits of `options`, as copies do.
+pub(crate) fn hash_blocks<R>(
+    read_at: R,
+    extents: &[Extent],
+    hasher: &BlockHasher,
+    options: &CopyOptions,
+) -> Result<()>
+where
+    R: Fn(u64, &mut [u8]) -> Result<()> + Sync,
+{
+    let blocks: Vec<Extent> = extents
+        .iter()
+        .flat_map(|extent| (extent.offset..extent.end()).step_by(BLOCK_SIZE as usize))
+        .map(|offset| Extent {
+            offset,
+            length: BLOCK_SIZE.min(hasher.size - offset),
+        })
+        .collect();
+
+    let next = AtomicU64::new(0);
+    let limiter = options.rate_limiter();
+    let stop = AtomicBool::new(false);
+    let failure = Mutex::new(None);
+    let jobs = options.jobs.clamp(1, blocks.len().max(1));
+    std::thread::scope(|scope| {
+        for _ in 0..jobs {
+            let (read_at, blocks, next, limiter, stop, failure) =
+                (&read_at, &blocks, &next, &limiter, &stop, &failure);
+            scope.spawn(move || {
+                options.apply_io_priority();
+                let mut buf = vec![0u8; BLOCK_SIZE as usize];
+                while !stop.load(Ordering::Relaxed) {
+                    let Some(block) = blocks.get(next.fetch_add(1, Ordering::Relaxed) as usize)
+                    else {
+                        break;
+                    };
+                    if let Some(limiter) = limiter {
+                        limiter.acquire(block.length);
+                    }
+                    let data = &mut buf[..block.length as usize];
+                    if let Err(e) =
+                        read_at(block.offset, data).and_then(|()| hasher.record(block.offset, data))
+                    {
+                        failure.lock().unwrap().get_or_insert(e);
+                        stop.store(true, Ordering::Relaxed);
+                        break;
+                    }
+                }
+            });
+        }
+    });
+    match failure.into_inner().unwrap() {
+        Some(e) => Err(e),
+        None => Ok(()),
+    }
+}
+
+/// Collects block digests as blocks are read, in any order and from any
+/// thread
+///
+/// With a journal, each digest is also appended to it once recorded, so
+/// blocks must be recorded only after they have been written.
+pub struct BlockHasher {
+    size: u64,
+    blocks: Mutex<Vec<Option<String>>>,
+    journal: Option<ConvertJournal>,
+}
+
+impl BlockHasher {
+    /// Hasher for a guest disk of `size` bytes
+    pub fn new(size: u64) -> Self {
+        Self {
+            size,
+            blocks: Mutex::new(vec![None; size.div_ceil(BLOCK_SIZE) as usize]),
+            journal: None,
+        }
+    }
+
+    /// Journal every block recorded from now on
+    pub fn with_journal(mut self, journal: ConvertJournal) -> Self {
+        self.journal = Some(journal);
+        self
+    }
+
+    /// `extents` widened to whole blocks, so that each block is read in one
+    /// piece
+    pub fn cover(&self, extents: &[Extent]) -> Vec<Extent> {
+        let mut covered: Vec<Extent> = Vec::with_capacity(extents.len());
+        for extent in extents {
+            let start = extent.offset / BLOCK_SIZE * BLOCK_SIZE;
+            let end = extent.end().next_multiple_of(BLOCK_SIZE).min(self.size);
+            match covered.last_mut() {
+                Some(last) if last.end() >= start => {
+                    last.length = end.max(last.end()) - last.offset
+                }
+                _ => covered.push(Extent {
+                    offset: start,
+                    length: end - start,
+                }),
+            }
+        }
+        covered
+    }
+
+    /// The blocks of `extents` (whole blocks) not recorded yet
+    pub fn pending(&self, extents: &[Extent]) -> Vec<Extent> {
+        let blocks = self.blocks.lock().unwrap();
+        let mut pending: Vec<Extent> = Vec::new();
+        for extent in extents {
+            for offset in (extent.offset..extent.end()).step_by(BLOCK_SIZE as usize) {
+                if blocks[(offset / BLOCK_SIZE) as usize].is_some() {
+                    continue;
+                }
+                let length = BLOCK_SIZE.min(self.size - offset);
+                match pending.last_mut() {
+                    Some(last) if last.end() == offset => last.length += length,
+                    _ => pending.push(Extent { offset, length }),
+                }
+            }
+        }
+        pending
+    }
+
+    /// Record the block starting at `offset`
+    pub fn record(&self, offset: u64, data: &[u8]) -> Result<()> {
+        debug_assert_eq!(offset % BLOCK_SIZE, 0);
+        let digest = format!("{:x}", Sha256::digest(data));
+        if let Some(journal) = &self.journal {
+            journal.append(&JournalBlock {
+                offset,
+                length: data.len() as u64,
+                sha256: digest.clone(),
+            })?;
+        }
+        self.restore(offset, digest);
+        Ok(())
+    }
+
+    /// Take the digest of the block starting at `offset` as known, from a
+    /// journal
+    pub fn restore(&self, offset: u64, digest: String) {
+        self.blocks.lock().unwrap()[(offset / BLOCK_SIZE) as usize] = Some(digest);
+    }
+
+    /// Digest of the block starting at `offset`, if recorded
+    pub fn digest(&self, offset: u64) -> Option<String> {
+        self.blocks.lock().unwrap()[(offset / BLOCK_SIZE) as usize].clone()
+    }
+
+    /// The manifest; blocks not recorded are zeros
+    pub fn finish(self) -> Manifest {
+        let size = self.size;
+        let zeros = format!("{:x}", Sha256::digest(vec![0u8; BLOCK_SIZE as usize]));
+        let blocks: Vec<String> = self
+            .blocks
+            .into_inner()
+            .unwrap()
+            .into_iter()
+            .enumerate()
+            .map(|(index, digest)| {
+                digest.unwrap_or_else(|| {
+                    let length = BLOCK_SIZE.min(size - index as u64 * BLOCK_SIZE);
+                    if length == BLOCK_SIZE {
+                        zeros.clone()
+                    } else {
+                        format!("{:x}", Sha256::digest(vec![0u8; length as usize]))
+                    }
+                })
+            })
+            .collect();
+
+        let mut digest = Sha256::new();
+        for block in &blocks {
+            digest.update(block.as_bytes());
+        }
+        Manifest {
+            algorithm: "sha256".to_string(),
+            block_size: BLOCK_SIZE,
+            size,
+            blocks,
+            digest: format!("{:x}", digest.finalize()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::FileExt;
+
+    fn image(path: &Path, size: u64, writes: &[(u64, u8)]) {
+        let file = std::fs::File::create(path).unwrap();
+        file.set_len(size).unwrap();
+        for &(offset, byte) in writes {
+            file.write_all_at(&[byte; 512], offset).unwrap();
+        }
+    }
+
+    #[test]
+    fn test_cover() {
+        let hasher = BlockHasher::new(3 * BLOCK_SIZE + 100);
+        let extent = |offset, length| Extent { offset, length };
+        assert_eq!(
+            hasher.cover(&[
+                extent(10, 20),
+                extent(BLOCK_SIZE - 1, 2),
+                extent(3 * BLOCK_SIZE + 5, 10)
+            ]),
+            vec![extent(0, 2 * BLOCK_SIZE), extent(3 * BLOCK_SIZE, 100)]
+        );
+    }
+
+    #[test]
+    fn test_pending() {
+        let hasher = BlockHasher::new(4 * BLOCK_SIZE + 100);
+        let extent = |offset, length| Extent { offset, length };
+        hasher.restore(BLOCK_SIZE, "a".to_string());
+        assert_eq!(
+            hasher.pending(&[extent(0, 3 * BLOCK_SIZE), extent(4 * BLOCK_SIZE, 100)]),
+            vec![
+                extent(0, BLOCK_SIZE),
+                extent(2 * BLOCK_SIZE, BLOCK_SIZE),
+                extent(4 * BLOCK_SIZE, 100)
+            ]
+        );
+    }
+
+    #[test]
+    fn test_manifest_and_verify() {
+        let dir = tempfile::tempdir().unwrap();
+        let size = 4 * BLOCK_SIZE + 4096;
+        let source = dir.path().join("source.raw");
+        image(
+            &source,
+            size,
+            &[(512, 1), (3 * BLOCK_SIZE, 2), (4 * BLOCK_SIZE, 3)],
+        );
+        let options = CopyOptions::default();
+
+        let manifest = Manifest::of_image(&source, &options).unwrap();
+        assert_eq!(manifest.blocks.len(), 5);
+        assert_eq!(manifest.size, size);
+        // Holes and written zeros hash alike
+        assert_eq!(manifest.blocks[1], manifest.blocks[2]);
+        assert_ne!(manifest.blocks[0], manifest.blocks[1]);
+
+        let copy = dir.path().join("copy.raw");
+        std::fs::copy(&source, &copy).unwrap();
+        let report = manifest.verify(&copy, &options).unwrap();
+        assert!(report.verified);
+        assert_eq!(report.source_digest, report.output_digest);
+
+        image(
+            &copy,
+            size,
+            &[(512, 1), (3 * BLOCK_SIZE, 9), (4 * BLOCK_SIZE, 3)],
+        );
+        let report = manifest.verify(&copy, &options).unwrap();
+        assert!(!report.verified);
+        assert_eq!(report.mismatches.len(), 1);
+        assert_eq!(report.mismatches[0].offset, 3 * BLOCK_SIZE);
+
+        let path = dir.path().join("manifest.json");
+        manifest.save(&path).unwrap();
+        assert_eq!(Manifest::load(&path).unwrap(), manifest);
+    }
+}
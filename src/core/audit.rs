@@ -0,0 +1,598 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Operation audit log
+//!
+//! Operations that change an image (rescue, repair, plan apply, worker jobs)
+//! append an entry to a JSON Lines file: what ran, on which image, with
+//! which parameters, on whose behalf, and how it ended. Every entry stores
+//! the hash of the previous one and a SHA-256 over its own contents, so
+//! editing, reordering or removing an entry breaks the chain and
+//! [`AuditLog::verify`] reports where.
+//!
+//! Dropping entries from the end of the log keeps the chain intact; compare
+//! [`AuditVerification::head`] with a copy kept elsewhere to detect that.
+
+use crate::core::{Error, Result};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::fs::{File, OpenOptions};
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::os::unix::io::AsRawFd;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Environment variable overriding the default log location
+pub const AUDIT_LOG_ENV: &str = "GUESTCTL_AUDIT_LOG";
+
+/// `prev_hash` of the first entry
+pub const GENESIS_HASH: &str = "0000000000000000000000000000000000000000000000000000000000000000";
+
+/// Who requested an operation
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Default)]
+pub struct AuditActor {
+    /// Local user or job submitter
+    pub user: String,
+
+    /// Host or address the request came from
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub source: Option<String>,
+
+    /// How the request was authorized (e.g., "oidc:alice@example.com")
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub authorization: Option<String>,
+}
+
+impl AuditActor {
+    /// Create an actor for `user`
+    pub fn new(user: impl Into<String>) -> Self {
+        Self {
+            user: user.into(),
+            ..Default::default()
+        }
+    }
+
+    /// The user running this process on this host
+    pub fn current() -> Self {
+        let user = std::env::var("USER")
+            .or_else(|_| std::env::var("LOGNAME"))
+            // SAFETY: getuid has no preconditions and cannot fail
+            .unwrap_or_else(|_| format!("uid:{}", unsafe { libc::getuid() }));
+        let source = std::fs::read_to_string("/proc/sys/kernel/hostname")
+            .ok()
+            .map(|name| name.trim().to_string())
+            .filter(|name| !name.is_empty());
+
+        Self {
+            user,
+            source,
+            authorization: None,
+        }
+    }
+
+    /// Set where the request came from
+    pub fn with_source(mut self, source: impl Into<String>) -> Self {
+        self.source = Some(source.into());
+        self
+    }
+
+    /// Set how the request was authorized
+    pub fn with_authorization(mut self, authorization: impl Into<String>) -> Self {
+        self.authorization = Some(authorization.into());
+        self
+    }
+}
+
+/// How an audited operation ended
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum AuditStatus {
+    Success,
+    Failure,
+    Cancelled,
+}
+
+/// Outcome of an audited operation
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditResult {
+    pub status: AuditStatus,
+
+    /// Error or summary
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub message: Option<String>,
+}
+
+impl AuditResult {
+    /// Successful operation
+    pub fn success() -> Self {
+        Self {
+            status: AuditStatus::Success,
+            message: None,
+        }
+    }
+
+    /// Failed operation
+    pub fn failure(message: impl Into<String>) -> Self {
+        Self {
+            status: AuditStatus::Failure,
+            message: Some(message.into()),
+        }
+    }
+
+    /// Operation cancelled before it finished
+    pub fn cancelled(message: impl Into<String>) -> Self {
+        Self {
+            status: AuditStatus::Cancelled,
+            message: Some(message.into()),
+        }
+    }
+
+    /// Add a summary or error message
+    pub fn with_message(mut self, message: impl Into<String>) -> Self {
+        self.message = Some(message.into());
+        self
+    }
+}
+
+/// An operation to record, before it is placed in the chain
+#[derive(Debug, Clone, PartialEq)]
+pub struct AuditRecord {
+    pub operation: String,
+    pub image: Option<String>,
+    pub image_digest: Option<String>,
+    pub parameters: Value,
+    pub actor: AuditActor,
+}
+
+impl AuditRecord {
+    /// Record `operation` requested by `actor`
+    pub fn new(operation: impl Into<String>, actor: AuditActor) -> Self {
+        Self {
+            operation: operation.into(),
+            image: None,
+            image_digest: None,
+            parameters: Value::Object(Default::default()),
+            actor,
+        }
+    }
+
+    /// Set the image the operation works on
+    pub fn with_image(mut self, image: impl Into<String>) -> Self {
+        self.image = Some(image.into());
+        self
+    }
+
+    /// Set the digest of the image (e.g., from [`image_digest`])
+    pub fn with_image_digest(mut self, digest: impl Into<String>) -> Self {
+        self.image_digest = Some(digest.into());
+        self
+    }
+
+    /// Set the operation parameters
+    ///
+    /// Leave out secrets such as passwords; the log is not encrypted.
+    pub fn with_parameters(mut self, parameters: Value) -> Self {
+        self.parameters = parameters;
+        self
+    }
+}
+
+/// An entry of the audit log
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditEntry {
+    /// Position in the log, starting at 1
+    pub sequence: u64,
+    pub timestamp: DateTime<Utc>,
+    pub operation: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub image_digest: Option<String>,
+    #[serde(default)]
+    pub parameters: Value,
+    pub actor: AuditActor,
+    pub result: AuditResult,
+    /// Hash of the previous entry ([`GENESIS_HASH`] for the first)
+    pub prev_hash: String,
+    /// SHA-256 of this entry without the `hash` field
+    pub hash: String,
+}
+
+/// Result of checking the hash chain of a log
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq)]
+pub struct AuditVerification {
+    /// Entries checked, up to the first broken one
+    pub entries: u64,
+
+    /// Hash of the last valid entry
+    pub head: String,
+
+    /// Line of the first entry that does not chain (1-based)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub broken_at: Option<u64>,
+
+    /// Why the chain breaks there
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub reason: Option<String>,
+}
+
+impl AuditVerification {
+    /// Whether every entry chains to the one before it
+    pub fn is_valid(&self) -> bool {
+        self.broken_at.is_none()
+    }
+}
+
+/// Append-only, hash-chained operation log
+#[derive(Debug)]
+pub struct AuditLog {
+    path: PathBuf,
+
+    /// Serializes appends within the process; `flock` covers other processes
+    lock: Mutex<()>,
+}
+
+impl AuditLog {
+    /// Open the log at `path` (created on the first append)
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        Self {
+            path: path.into(),
+            lock: Mutex::new(()),
+        }
+    }
+
+    /// Default log location
+    ///
+    /// `$GUESTCTL_AUDIT_LOG`, or `guestctl/audit.jsonl` in the user's local
+    /// data directory.
+    pub fn default_path() -> PathBuf {
+        if let Some(path) = std::env::var_os(AUDIT_LOG_ENV) {
+            return PathBuf::from(path);
+        }
+
+        dirs::data_local_dir()
+            .unwrap_or_else(std::env::temp_dir)
+            .join("guestctl")
+            .join("audit.jsonl")
+    }
+
+    /// Path of the log file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Append an entry chained to the current last one
+    pub fn append(&self, record: AuditRecord, result: AuditResult) -> Result<AuditEntry> {
+        let _guard = self.lock.lock().unwrap_or_else(|e| e.into_inner());
+
+        if let Some(dir) = self.path.parent().filter(|dir| !dir.as_os_str().is_empty()) {
+            std::fs::create_dir_all(dir)?;
+        }
+        let mut file = OpenOptions::new()
+            .read(true)
+            .append(true)
+            .create(true)
+            .open(&self.path)?;
+        // Released when the file is closed at the end of the append
+        lock_exclusive(&file)?;
+
+        let (sequence, prev_hash) = match last_line(&mut file)? {
+            Some(line) => {
+                let last: Value = serde_json::from_str(&line).map_err(|e| {
+                    Error::InvalidFormat(format!("Corrupt last audit log entry: {}", e))
+                })?;
+                let sequence = last["sequence"].as_u64().unwrap_or(0);
+                let hash = last["hash"].as_str().unwrap_or_default().to_string();
+                (sequence + 1, hash)
+            }
+            None => (1, GENESIS_HASH.to_string()),
+        };
+
+        let mut entry = AuditEntry {
+            sequence,
+            timestamp: Utc::now(),
+            operation: record.operation,
+            image: record.image,
+            image_digest: record.image_digest,
+            parameters: record.parameters,
+            actor: record.actor,
+            result,
+            prev_hash,
+            hash: String::new(),
+        };
+        let value = serde_json::to_value(&entry)
+            .map_err(|e| Error::InvalidFormat(format!("Failed to encode audit entry: {}", e)))?;
+        entry.hash = entry_hash(&value);
+
+        let mut line = serde_json::to_string(&entry)
+            .map_err(|e| Error::InvalidFormat(format!("Failed to encode audit entry: {}", e)))?;
+        line.push('\n');
+        file.write_all(line.as_bytes())?;
+        file.sync_data()?;
+
+        Ok(entry)
+    }
+
+    /// All entries, oldest first
+    pub fn entries(&self) -> Result<Vec<AuditEntry>> {
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(e.into()),
+        };
+
+        let mut entries = Vec::new();
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let entry = serde_json::from_str(&line).map_err(|e| {
+                Error::InvalidFormat(format!("Audit log line {}: {}", index + 1, e))
+            })?;
+            entries.push(entry);
+        }
+        Ok(entries)
+    }
+
+    /// Check the hash chain
+    ///
+    /// Entries are checked as written, so fields added by newer versions
+    /// are covered by the hash too.
+    pub fn verify(&self) -> Result<AuditVerification> {
+        let mut verification = AuditVerification {
+            entries: 0,
+            head: GENESIS_HASH.to_string(),
+            broken_at: None,
+            reason: None,
+        };
+
+        let file = match File::open(&self.path) {
+            Ok(file) => file,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(verification),
+            Err(e) => return Err(e.into()),
+        };
+
+        for (index, line) in BufReader::new(file).lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            if let Err(reason) = check_entry(&line, verification.entries + 1, &verification.head) {
+                verification.broken_at = Some(index as u64 + 1);
+                verification.reason = Some(reason);
+                break;
+            }
+            verification.entries += 1;
+            verification.head = serde_json::from_str::<Value>(&line)
+                .ok()
+                .and_then(|value| value["hash"].as_str().map(str::to_string))
+                .unwrap_or_default();
+        }
+
+        Ok(verification)
+    }
+
+    /// All entries as a pretty-printed JSON array
+    pub fn export_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(&self.entries()?)
+            .map_err(|e| Error::InvalidFormat(format!("Failed to encode audit log: {}", e)))
+    }
+}
+
+/// SHA-256 of an image file as "sha256:<hex>"
+///
+/// Reads the whole image, so call it once per operation.
+pub fn image_digest(path: &Path) -> Result<String> {
+    let mut file = File::open(path)?;
+    let mut hasher = Sha256::new();
+    let mut buffer = vec![0u8; 1024 * 1024];
+    loop {
+        let read = file.read(&mut buffer)?;
+        if read == 0 {
+            break;
+        }
+        hasher.update(&buffer[..read]);
+    }
+    Ok(format!("sha256:{:x}", hasher.finalize()))
+}
+
+/// Check one log line against the expected position and previous hash
+fn check_entry(line: &str, sequence: u64, prev_hash: &str) -> std::result::Result<(), String> {
+    let value: Value = serde_json::from_str(line).map_err(|e| format!("not JSON: {}", e))?;
+
+    if value["sequence"].as_u64() != Some(sequence) {
+        return Err(format!(
+            "expected sequence {}, found {}",
+            sequence, value["sequence"]
+        ));
+    }
+    if value["prev_hash"].as_str() != Some(prev_hash) {
+        return Err("previous hash does not match the entry before it".to_string());
+    }
+    if value["hash"].as_str() != Some(entry_hash(&value).as_str()) {
+        return Err("entry hash does not match its contents".to_string());
+    }
+    Ok(())
+}
+
+/// SHA-256 over the canonical JSON of an entry, leaving out `hash`
+fn entry_hash(entry: &Value) -> String {
+    let mut canonical = String::new();
+    match entry {
+        Value::Object(map) => {
+            let without_hash: serde_json::Map<String, Value> = map
+                .iter()
+                .filter(|(key, _)| key.as_str() != "hash")
+                .map(|(key, value)| (key.clone(), value.clone()))
+                .collect();
+            write_canonical(&Value::Object(without_hash), &mut canonical);
+        }
+        other => write_canonical(other, &mut canonical),
+    }
+    format!("{:x}", Sha256::digest(canonical.as_bytes()))
+}
+
+/// JSON with object keys sorted, independent of how the map was built
+fn write_canonical(value: &Value, out: &mut String) {
+    match value {
+        Value::Object(map) => {
+            let mut keys: Vec<&String> = map.keys().collect();
+            keys.sort();
+            out.push('{');
+            for (i, key) in keys.into_iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                out.push_str(&Value::String(key.clone()).to_string());
+                out.push(':');
+                write_canonical(&map[key], out);
+            }
+            out.push('}');
+        }
+        Value::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_canonical(item, out);
+            }
+            out.push(']');
+        }
+        other => out.push_str(&other.to_string()),
+    }
+}
+
+/// Last non-empty line of a file, read from the end
+fn last_line(file: &mut File) -> Result<Option<String>> {
+    let len = file.seek(SeekFrom::End(0))?;
+    let mut window = 8192u64;
+
+    loop {
+        let start = len.saturating_sub(window);
+        file.seek(SeekFrom::Start(start))?;
+        let mut tail = Vec::with_capacity((len - start) as usize);
+        Read::by_ref(file)
+            .take(len - start)
+            .read_to_end(&mut tail)?;
+
+        let text = String::from_utf8_lossy(&tail);
+        let trimmed = text.trim_end();
+        match trimmed.rfind('\n') {
+            Some(pos) => return Ok(Some(trimmed[pos + 1..].to_string())),
+            None if start == 0 => {
+                return Ok((!trimmed.is_empty()).then(|| trimmed.to_string()));
+            }
+            None => window *= 2,
+        }
+    }
+}
+
+/// Take an exclusive `flock` on a file
+fn lock_exclusive(file: &File) -> Result<()> {
+    // SAFETY: the descriptor is valid for the duration of the call
+    if unsafe { libc::flock(file.as_raw_fd(), libc::LOCK_EX) } != 0 {
+        return Err(std::io::Error::last_os_error().into());
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+    use tempfile::TempDir;
+
+    fn record(operation: &str) -> AuditRecord {
+        AuditRecord::new(operation, AuditActor::new("alice").with_source("host1"))
+            .with_image("/vms/web.qcow2")
+            .with_image_digest("sha256:abc")
+            .with_parameters(json!({"operation": "reset-password", "user": "root"}))
+    }
+
+    #[test]
+    fn test_append_chains_entries() {
+        let dir = TempDir::new().unwrap();
+        let log = AuditLog::new(dir.path().join("audit/audit.jsonl"));
+
+        let first = log
+            .append(record("rescue"), AuditResult::success())
+            .unwrap();
+        let second = log
+            .append(record("repair"), AuditResult::failure("mount failed"))
+            .unwrap();
+
+        assert_eq!(first.sequence, 1);
+        assert_eq!(first.prev_hash, GENESIS_HASH);
+        assert_eq!(second.sequence, 2);
+        assert_eq!(second.prev_hash, first.hash);
+
+        let entries = log.entries().unwrap();
+        assert_eq!(entries, vec![first, second.clone()]);
+
+        let verification = log.verify().unwrap();
+        assert!(verification.is_valid());
+        assert_eq!(verification.entries, 2);
+        assert_eq!(verification.head, second.hash);
+
+        let exported: Vec<AuditEntry> = serde_json::from_str(&log.export_json().unwrap()).unwrap();
+        assert_eq!(exported.len(), 2);
+    }
+
+    #[test]
+    fn test_verify_detects_tampering() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("audit.jsonl");
+        let log = AuditLog::new(&path);
+        for operation in ["rescue", "repair", "plan.apply"] {
+            log.append(record(operation), AuditResult::success())
+                .unwrap();
+        }
+
+        // Edit the result of the second entry
+        let content = std::fs::read_to_string(&path).unwrap();
+        let lines: Vec<&str> = content.lines().collect();
+        let edited = lines[1].replace("\"success\"", "\"failure\"");
+        std::fs::write(&path, format!("{}\n{}\n{}\n", lines[0], edited, lines[2])).unwrap();
+
+        let verification = log.verify().unwrap();
+        assert!(!verification.is_valid());
+        assert_eq!(verification.entries, 1);
+        assert_eq!(verification.broken_at, Some(2));
+
+        // Drop the second entry
+        std::fs::write(&path, format!("{}\n{}\n", lines[0], lines[2])).unwrap();
+        let verification = log.verify().unwrap();
+        assert_eq!(verification.broken_at, Some(2));
+        assert!(verification.reason.unwrap().contains("sequence"));
+    }
+
+    #[test]
+    fn test_hash_ignores_key_order() {
+        let a = json!({"sequence": 1, "parameters": {"b": 1, "a": [2, {"y": 1, "x": 0}]}});
+        let b = json!({"parameters": {"a": [2, {"x": 0, "y": 1}], "b": 1}, "sequence": 1});
+        assert_eq!(entry_hash(&a), entry_hash(&b));
+    }
+
+    #[test]
+    fn test_empty_log() {
+        let dir = TempDir::new().unwrap();
+        let log = AuditLog::new(dir.path().join("missing.jsonl"));
+        assert!(log.entries().unwrap().is_empty());
+        let verification = log.verify().unwrap();
+        assert!(verification.is_valid());
+        assert_eq!(verification.head, GENESIS_HASH);
+    }
+
+    #[test]
+    fn test_image_digest() {
+        let dir = TempDir::new().unwrap();
+        let path = dir.path().join("disk.img");
+        std::fs::write(&path, b"").unwrap();
+        assert_eq!(
+            image_digest(&path).unwrap(),
+            "sha256:e3b0c44298fc1c149afbf4c8996fb92427ae41e4649b934ca495991b7852b855"
+        );
+    }
+}
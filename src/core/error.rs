@@ -55,8 +55,18 @@ pub enum Error {
     #[error("Resource limit exceeded: {0}")]
     ResourceLimit(String),
 
+    #[error("Registry error: {0}")]
+    Registry(String),
+
+    #[error("Publish error: {0}")]
+    Publish(String),
+
     #[error("Unknown error: {0}")]
     Unknown(String),
+
+    /// Error of a call replayed from a session trace, as it was recorded
+    #[error("{0}")]
+    Replayed(String),
 }
 
 /// Result type alias for guestctl operations
@@ -0,0 +1,49 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Escaping text for XML documents and shell command lines
+
+/// Escape `text` for XML character data and attribute values
+pub fn xml_escape(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+        .replace('\'', "&apos;")
+        .replace('"', "&quot;")
+}
+
+/// Quote `arg` for a POSIX shell unless it is plainly safe
+pub fn shell_quote(arg: &str) -> String {
+    let safe = !arg.is_empty()
+        && arg
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || "-_./,=:+@%".contains(c));
+    if safe {
+        arg.to_string()
+    } else {
+        format!("'{}'", arg.replace('\'', "'\\''"))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_xml_escape() {
+        assert_eq!(xml_escape("web & db"), "web &amp; db");
+        assert_eq!(
+            xml_escape("<a href='x'>\"</a>"),
+            "&lt;a href=&apos;x&apos;&gt;&quot;&lt;/a&gt;"
+        );
+    }
+
+    #[test]
+    fn test_shell_quote() {
+        assert_eq!(
+            shell_quote("/var/lib/libvirt/images/web.qcow2"),
+            "/var/lib/libvirt/images/web.qcow2"
+        );
+        assert_eq!(shell_quote("My Disk.vmdk"), "'My Disk.vmdk'");
+        assert_eq!(shell_quote("it's"), "'it'\\''s'");
+        assert_eq!(shell_quote(""), "''");
+    }
+}
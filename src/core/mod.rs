@@ -1,15 +1,18 @@
 // SPDX-License-Identifier: LGPL-3.0-or-later
 //! Core utilities and types for guestctl
 
+pub mod audit;
 pub mod binary_cache;
 pub mod diagnostics;
 pub mod error;
+pub mod escape;
 pub mod mem_optimize;
 pub mod progress;
 pub mod retry;
 pub mod systemd;
 pub mod types;
 
+pub use audit::{AuditActor, AuditEntry, AuditLog, AuditRecord, AuditResult, AuditStatus};
 pub use binary_cache::{BinaryCache, CachedInspection, CacheStats};
 pub use diagnostics::DiagnosticError;
 pub use error::{Error, Result};
@@ -5,8 +5,10 @@ use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
 pub mod journal;
+pub mod journal_file;
 pub mod services;
 pub mod boot;
+pub mod budget;
 
 /// Systemd journal entry
 #[derive(Debug, Clone)]
@@ -41,6 +43,11 @@ impl JournalEntry {
         }
     }
 
+    /// Boot the entry was logged in
+    pub fn boot_id(&self) -> Option<&str> {
+        self.fields.get("_BOOT_ID").map(String::as_str)
+    }
+
     /// Format timestamp as human-readable string
     pub fn timestamp_str(&self) -> String {
         let secs = self.timestamp / 1_000_000;
@@ -1,6 +1,7 @@
 // SPDX-License-Identifier: LGPL-3.0-or-later
 //! Systemd boot analysis
 
+use super::budget::parse_duration;
 use super::{BootTiming, ServiceTiming, SystemdAnalyzer};
 use anyhow::{Context, Result};
 use std::fs;
@@ -24,7 +25,15 @@ impl BootAnalyzer {
         let analyze_file = self.analyzer.root_path.join("var/lib/systemd/analyze-blame.txt");
 
         if analyze_file.exists() {
-            return self.parse_analyze_file(&analyze_file);
+            let mut timing = self.parse_analyze_file(&analyze_file)?;
+
+            // `systemd-analyze time` output adds the phase split
+            let time_file = self.analyzer.root_path.join("var/lib/systemd/analyze-time.txt");
+            if let Ok(content) = fs::read_to_string(&time_file) {
+                Self::apply_startup_time(&content, &mut timing);
+            }
+
+            return Ok(timing);
         }
 
         // Otherwise, create estimated timing
@@ -67,6 +76,34 @@ impl BootAnalyzer {
         })
     }
 
+    /// Apply a "Startup finished in 2.1s (kernel) + 3s (initrd) + 9.8s (userspace) = 14.9s" line
+    fn apply_startup_time(content: &str, timing: &mut BootTiming) {
+        let Some(line) = content.lines().find(|l| l.starts_with("Startup finished in ")) else {
+            return;
+        };
+        let line = line.trim_start_matches("Startup finished in ");
+        let (phases, total) = line.split_once(" = ").unwrap_or((line, ""));
+
+        for phase in phases.split(" + ") {
+            let Some((duration, name)) = phase.trim().rsplit_once(" (") else {
+                continue;
+            };
+            let Some(ms) = parse_duration(duration) else {
+                continue;
+            };
+            match name.trim_end_matches(')') {
+                "kernel" => timing.kernel_time = ms,
+                "initrd" => timing.initrd_time = ms,
+                "userspace" => timing.userspace_time = ms,
+                _ => {}
+            }
+        }
+
+        if let Some(ms) = parse_duration(total.trim()) {
+            timing.total_time = ms;
+        }
+    }
+
     /// Parse time string (e.g., "1.234s" or "567ms")
     fn parse_time_str(&self, s: &str) -> Option<u64> {
         // Check for milliseconds FIRST (before seconds, since "ms" ends with "s")
@@ -248,6 +285,27 @@ mod tests {
         assert_eq!(boot_analyzer.parse_time_str("invalid"), None);
     }
 
+    #[test]
+    fn test_apply_startup_time() {
+        let mut timing = BootTiming {
+            total_time: 4000,
+            kernel_time: 0,
+            initrd_time: 0,
+            userspace_time: 4000,
+            services: Vec::new(),
+        };
+
+        BootAnalyzer::apply_startup_time(
+            "Startup finished in 1.2s (kernel) + 2.5s (initrd) + 1min 3.1s (userspace) = 1min 6.8s\n\
+             graphical.target reached after 1min 2.9s in userspace.\n",
+            &mut timing,
+        );
+        assert_eq!(timing.kernel_time, 1200);
+        assert_eq!(timing.initrd_time, 2500);
+        assert_eq!(timing.userspace_time, 63100);
+        assert_eq!(timing.total_time, 66800);
+    }
+
     #[test]
     fn test_get_recommendations() {
         let analyzer = SystemdAnalyzer::new("/tmp");
@@ -0,0 +1,428 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Boot time budgets
+//!
+//! A budget file declares how long the boot, its phases and individual
+//! services may take:
+//!
+//! ```yaml
+//! total: 20s
+//! userspace: 12s
+//! default_service: 3s
+//! services:
+//!   NetworkManager-wait-online.service: 1s
+//!   "*.mount": 500ms
+//! ```
+//!
+//! Durations take a `us`, `ms`, `s` or `min` suffix (`1min 30s` also works);
+//! bare numbers are seconds. Service keys may be glob patterns; an exact name
+//! wins over a pattern, and `default_service` covers everything else.
+
+use super::BootTiming;
+use anyhow::{Context, Result};
+use serde::{Deserialize, Deserializer, Serialize};
+use std::collections::BTreeMap;
+use std::path::Path;
+
+/// Units commonly masked or disabled to speed up boot: (pattern, action, reason)
+const CANDIDATES: &[(&str, &str, &str)] = &[
+    (
+        "*-wait-online.service",
+        "disable",
+        "waits for full network connectivity, which few services need",
+    ),
+    (
+        "systemd-udev-settle.service",
+        "mask",
+        "deprecated; waits for every device to be probed",
+    ),
+    (
+        "plymouth-quit-wait.service",
+        "mask",
+        "holds the boot splash until a login prompt starts",
+    ),
+    (
+        "apt-daily*.service",
+        "disable",
+        "refreshes package lists; its timer can run it later",
+    ),
+    (
+        "dnf-makecache.service",
+        "disable",
+        "refreshes repository metadata; its timer can run it later",
+    ),
+    (
+        "man-db.service",
+        "disable",
+        "rebuilds the manual page index",
+    ),
+    (
+        "snapd.seeded.service",
+        "disable",
+        "waits for snaps to be seeded",
+    ),
+    (
+        "ModemManager.service",
+        "disable",
+        "probes for mobile broadband modems",
+    ),
+    (
+        "lvm2-monitor.service",
+        "disable",
+        "only needed for LVM snapshots and mirrors",
+    ),
+    (
+        "cups.service",
+        "disable",
+        "printing is rarely needed on servers",
+    ),
+    (
+        "bluetooth.service",
+        "disable",
+        "Bluetooth is rarely needed on servers",
+    ),
+    (
+        "avahi-daemon.service",
+        "disable",
+        "mDNS discovery is rarely needed on servers",
+    ),
+];
+
+/// Boot time budget (all durations in milliseconds)
+#[derive(Debug, Clone, Default, Deserialize)]
+#[serde(deny_unknown_fields)]
+pub struct BootBudget {
+    /// Whole boot
+    #[serde(default, deserialize_with = "optional_duration")]
+    pub total: Option<u64>,
+    /// Kernel phase
+    #[serde(default, deserialize_with = "optional_duration")]
+    pub kernel: Option<u64>,
+    /// Initrd phase
+    #[serde(default, deserialize_with = "optional_duration")]
+    pub initrd: Option<u64>,
+    /// Userspace phase
+    #[serde(default, deserialize_with = "optional_duration")]
+    pub userspace: Option<u64>,
+    /// Budget for services not listed in `services`
+    #[serde(default, deserialize_with = "optional_duration")]
+    pub default_service: Option<u64>,
+    /// Per-service budgets, keyed by unit name or glob pattern
+    #[serde(default, deserialize_with = "duration_map")]
+    pub services: BTreeMap<String, u64>,
+}
+
+/// A boot phase or service that went over budget
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct BudgetViolation {
+    /// `total`, `kernel`, `initrd`, `userspace` or `service`
+    pub scope: String,
+    /// Service unit name
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub unit: Option<String>,
+    /// Measured time (ms)
+    pub actual: u64,
+    /// Allowed time (ms)
+    pub budget: u64,
+}
+
+impl BudgetViolation {
+    /// Time over budget (ms)
+    pub fn excess(&self) -> u64 {
+        self.actual - self.budget
+    }
+}
+
+/// A unit that could be masked or disabled to save boot time
+#[derive(Debug, Clone, PartialEq, Serialize)]
+pub struct MaskCandidate {
+    pub unit: String,
+    /// `mask` or `disable`
+    pub action: String,
+    /// Activation time the unit costs (ms)
+    pub saves: u64,
+    pub reason: String,
+}
+
+/// Result of checking boot timings against a budget
+#[derive(Debug, Clone, Serialize)]
+pub struct BudgetReport {
+    /// Measured boot time (ms)
+    pub total: u64,
+    pub violations: Vec<BudgetViolation>,
+    /// Suggestions, largest saving first; empty when the budget is met
+    pub candidates: Vec<MaskCandidate>,
+    /// Budgeted phases the timings do not cover
+    pub unchecked: Vec<String>,
+}
+
+impl BudgetReport {
+    /// Whether every budget was met
+    pub fn passed(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+impl BootBudget {
+    /// Load a budget from a YAML file
+    pub fn load(path: &Path) -> Result<Self> {
+        let content = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read budget file: {}", path.display()))?;
+        Self::from_yaml(&content)
+            .with_context(|| format!("Invalid budget file: {}", path.display()))
+    }
+
+    /// Parse a budget from YAML
+    pub fn from_yaml(content: &str) -> Result<Self> {
+        let budget: Self = serde_yaml::from_str(content)?;
+        for pattern in budget.services.keys() {
+            glob::Pattern::new(pattern)
+                .with_context(|| format!("Invalid service pattern: {}", pattern))?;
+        }
+        Ok(budget)
+    }
+
+    /// Budget that applies to a service, if any
+    pub fn service_budget(&self, name: &str) -> Option<u64> {
+        if let Some(&budget) = self.services.get(name) {
+            return Some(budget);
+        }
+        self.services
+            .iter()
+            .find(|(pattern, _)| {
+                glob::Pattern::new(pattern)
+                    .map(|p| p.matches(name))
+                    .unwrap_or(false)
+            })
+            .map(|(_, &budget)| budget)
+            .or(self.default_service)
+    }
+
+    /// Check boot timings against the budget
+    pub fn evaluate(&self, timing: &BootTiming) -> BudgetReport {
+        let mut violations = Vec::new();
+        let mut unchecked = Vec::new();
+
+        let phases = [
+            ("total", self.total, timing.total_time),
+            ("kernel", self.kernel, timing.kernel_time),
+            ("initrd", self.initrd, timing.initrd_time),
+            ("userspace", self.userspace, timing.userspace_time),
+        ];
+        for (scope, budget, actual) in phases {
+            let Some(budget) = budget else { continue };
+            // Blame output alone has no kernel/initrd split
+            if actual == 0 && matches!(scope, "kernel" | "initrd") {
+                unchecked.push(scope.to_string());
+            } else if actual > budget {
+                violations.push(BudgetViolation {
+                    scope: scope.to_string(),
+                    unit: None,
+                    actual,
+                    budget,
+                });
+            }
+        }
+
+        for service in timing.slowest_services(timing.services.len()) {
+            if let Some(budget) = self.service_budget(&service.name) {
+                if service.activation_time > budget {
+                    violations.push(BudgetViolation {
+                        scope: "service".to_string(),
+                        unit: Some(service.name.clone()),
+                        actual: service.activation_time,
+                        budget,
+                    });
+                }
+            }
+        }
+
+        let candidates = if violations.is_empty() {
+            Vec::new()
+        } else {
+            mask_candidates(timing)
+        };
+
+        BudgetReport {
+            total: timing.total_time,
+            violations,
+            candidates,
+            unchecked,
+        }
+    }
+}
+
+/// Known slow-but-optional units in the boot, largest saving first
+fn mask_candidates(timing: &BootTiming) -> Vec<MaskCandidate> {
+    timing
+        .slowest_services(timing.services.len())
+        .into_iter()
+        .filter(|service| service.activation_time > 0)
+        .filter_map(|service| {
+            CANDIDATES
+                .iter()
+                .find(|(pattern, _, _)| {
+                    glob::Pattern::new(pattern)
+                        .map(|p| p.matches(&service.name))
+                        .unwrap_or(false)
+                })
+                .map(|(_, action, reason)| MaskCandidate {
+                    unit: service.name.clone(),
+                    action: action.to_string(),
+                    saves: service.activation_time,
+                    reason: reason.to_string(),
+                })
+        })
+        .collect()
+}
+
+/// Parse a duration such as `1.5s`, `500ms` or `1min 2.5s` into milliseconds
+///
+/// A bare number is taken as seconds.
+pub fn parse_duration(s: &str) -> Option<u64> {
+    let mut total = 0.0;
+    let mut parts = 0;
+
+    for part in s.split_whitespace() {
+        let split = part
+            .find(|c: char| !(c.is_ascii_digit() || c == '.'))
+            .unwrap_or(part.len());
+        let (number, unit) = part.split_at(split);
+        let value: f64 = number.parse().ok()?;
+        let scale = match unit {
+            "us" | "µs" => 0.001,
+            "ms" => 1.0,
+            "" | "s" => 1000.0,
+            "min" => 60_000.0,
+            "h" => 3_600_000.0,
+            _ => return None,
+        };
+        total += value * scale;
+        parts += 1;
+    }
+
+    (parts > 0).then(|| total.round() as u64)
+}
+
+/// Duration given as a number of seconds or a string with units
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum DurationValue {
+    Seconds(f64),
+    Text(String),
+}
+
+impl DurationValue {
+    fn millis<E: serde::de::Error>(self) -> Result<u64, E> {
+        match self {
+            Self::Seconds(secs) if secs >= 0.0 => Ok((secs * 1000.0).round() as u64),
+            Self::Seconds(secs) => Err(E::custom(format!("negative duration: {}", secs))),
+            Self::Text(text) => parse_duration(&text)
+                .ok_or_else(|| E::custom(format!("invalid duration: {}", text))),
+        }
+    }
+}
+
+fn optional_duration<'de, D: Deserializer<'de>>(d: D) -> Result<Option<u64>, D::Error> {
+    Option::<DurationValue>::deserialize(d)?
+        .map(DurationValue::millis)
+        .transpose()
+}
+
+fn duration_map<'de, D: Deserializer<'de>>(d: D) -> Result<BTreeMap<String, u64>, D::Error> {
+    BTreeMap::<String, DurationValue>::deserialize(d)?
+        .into_iter()
+        .map(|(name, value)| Ok((name, value.millis()?)))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::systemd::ServiceTiming;
+
+    fn timing() -> BootTiming {
+        let services = [
+            ("NetworkManager-wait-online.service", 6200),
+            ("app.service", 2500),
+            ("boot.mount", 800),
+            ("sshd.service", 300),
+        ];
+        BootTiming {
+            total_time: 9800,
+            kernel_time: 0,
+            initrd_time: 0,
+            userspace_time: 9800,
+            services: services
+                .iter()
+                .map(|(name, ms)| ServiceTiming {
+                    name: name.to_string(),
+                    activation_time: *ms,
+                    start_offset: 0,
+                })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_parse_duration() {
+        assert_eq!(parse_duration("1.5s"), Some(1500));
+        assert_eq!(parse_duration("500ms"), Some(500));
+        assert_eq!(parse_duration("1min 2.5s"), Some(62500));
+        assert_eq!(parse_duration("20"), Some(20000));
+        assert_eq!(parse_duration("850us"), Some(1));
+        assert_eq!(parse_duration("fast"), None);
+        assert_eq!(parse_duration(""), None);
+    }
+
+    #[test]
+    fn test_budget_from_yaml() {
+        let budget = BootBudget::from_yaml(
+            "total: 20s\nkernel: 4\ndefault_service: 3s\nservices:\n  \"*.mount\": 500ms\n",
+        )
+        .unwrap();
+        assert_eq!(budget.total, Some(20000));
+        assert_eq!(budget.kernel, Some(4000));
+        assert_eq!(budget.service_budget("boot.mount"), Some(500));
+        assert_eq!(budget.service_budget("sshd.service"), Some(3000));
+
+        assert!(BootBudget::from_yaml("total: soon\n").is_err());
+        assert!(BootBudget::from_yaml("totl: 5s\n").is_err());
+    }
+
+    #[test]
+    fn test_evaluate_budget() {
+        let budget = BootBudget::from_yaml(
+            "total: 8s\n\
+             kernel: 3s\n\
+             default_service: 3s\n\
+             services:\n  app.service: 3s\n  \"*.mount\": 500ms\n",
+        )
+        .unwrap();
+        let report = budget.evaluate(&timing());
+
+        assert!(!report.passed());
+        let failed: Vec<_> = report
+            .violations
+            .iter()
+            .map(|v| v.unit.as_deref().unwrap_or(&v.scope))
+            .collect();
+        assert_eq!(
+            failed,
+            vec!["total", "NetworkManager-wait-online.service", "boot.mount"]
+        );
+        assert_eq!(report.violations[0].excess(), 1800);
+        assert_eq!(report.unchecked, vec!["kernel"]);
+
+        assert_eq!(report.candidates.len(), 1);
+        assert_eq!(
+            report.candidates[0].unit,
+            "NetworkManager-wait-online.service"
+        );
+        assert_eq!(report.candidates[0].action, "disable");
+
+        let relaxed = BootBudget::from_yaml("total: 10s\n").unwrap();
+        let report = relaxed.evaluate(&timing());
+        assert!(report.passed());
+        assert!(report.candidates.is_empty());
+    }
+}
@@ -1,11 +1,12 @@
 // SPDX-License-Identifier: LGPL-3.0-or-later
 //! Systemd journal reading and analysis
 
+use super::journal_file::{is_journal_file, JournalFile, JournalHeader, RawEntry, SealStatus};
 use super::{JournalEntry, SystemdAnalyzer};
-use anyhow::{Context, Result};
-use std::collections::HashMap;
+use anyhow::{bail, Context, Result};
+use std::collections::{HashMap, HashSet};
 use std::fs;
-use std::path::Path;
+use std::path::{Path, PathBuf};
 
 /// Journal filter options
 #[derive(Debug, Clone, Default)]
@@ -20,6 +21,106 @@ pub struct JournalFilter {
     pub until: Option<u64>,
     /// Maximum number of entries to return
     pub limit: Option<usize>,
+    /// Only this boot: a boot ID, or an offset as `journalctl -b` takes
+    /// it (0 the last boot, -1 the one before, 1 the first)
+    pub boot: Option<String>,
+}
+
+/// One boot's span of the journal
+#[derive(Debug, Clone)]
+pub struct BootInfo {
+    /// Offset from the last boot: 0, -1, -2, ...
+    pub offset: i64,
+    pub boot_id: String,
+    /// First and last entry (microseconds since epoch)
+    pub first: u64,
+    pub last: u64,
+    pub entries: usize,
+}
+
+/// What `JournalReader::verify` found in one file
+#[derive(Debug, Clone)]
+pub struct JournalFileReport {
+    pub path: PathBuf,
+    /// None if the file is not a readable journal
+    pub header: Option<JournalHeader>,
+    pub entries: usize,
+    pub seal: SealStatus,
+    pub undecoded_fields: usize,
+    pub truncated: bool,
+    pub problems: Vec<String>,
+}
+
+/// Whether `path` is a journal exported as text (`.txt` or `.export`)
+fn is_exported(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|ext| ext == "txt" || ext == "export")
+}
+
+/// Boots of time-ordered entries, oldest first
+fn list_boots(entries: &[JournalEntry]) -> Vec<BootInfo> {
+    let mut boots: Vec<BootInfo> = Vec::new();
+    let mut index: HashMap<String, usize> = HashMap::new();
+    for entry in entries {
+        let Some(boot_id) = entry.boot_id() else {
+            continue;
+        };
+        match index.get(boot_id) {
+            Some(&i) => {
+                let boot = &mut boots[i];
+                boot.last = boot.last.max(entry.timestamp);
+                boot.entries += 1;
+            }
+            None => {
+                index.insert(boot_id.to_string(), boots.len());
+                boots.push(BootInfo {
+                    offset: 0,
+                    boot_id: boot_id.to_string(),
+                    first: entry.timestamp,
+                    last: entry.timestamp,
+                    entries: 1,
+                });
+            }
+        }
+    }
+
+    let count = boots.len() as i64;
+    for (i, boot) in boots.iter_mut().enumerate() {
+        boot.offset = i as i64 + 1 - count;
+    }
+    boots
+}
+
+/// The boot ID `boot` names: an ID, a unique ID prefix, or an offset
+fn resolve_boot(boots: &[BootInfo], boot: &str) -> Result<String> {
+    let boot = boot.trim();
+    if let Ok(offset) = boot.parse::<i64>() {
+        // Positive offsets count from the first boot, as in journalctl
+        let found = if offset > 0 {
+            boots.get(offset as usize - 1)
+        } else {
+            boots.iter().find(|b| b.offset == offset)
+        };
+        return match found {
+            Some(found) => Ok(found.boot_id.clone()),
+            None => bail!(
+                "No boot at offset {} ({} boots in journal)",
+                offset,
+                boots.len()
+            ),
+        };
+    }
+
+    let id = boot.replace('-', "").to_lowercase();
+    let matches: Vec<&BootInfo> = boots
+        .iter()
+        .filter(|b| b.boot_id.starts_with(&id))
+        .collect();
+    match matches.as_slice() {
+        [found] => Ok(found.boot_id.clone()),
+        [] => bail!("Boot {} not found in journal", boot),
+        _ => bail!("Boot ID prefix {} is ambiguous", boot),
+    }
 }
 
 /// Journal statistics
@@ -86,51 +187,152 @@ impl JournalReader {
 
     /// Read journal entries with filter
     ///
-    /// Note: This is a simplified implementation that reads text-based journal files.
-    /// For binary journal files (.journal), full implementation would require
-    /// libsystemd bindings or custom binary parser.
+    /// Reads every binary journal under the journal directory, including
+    /// rotated (`system@*.journal`), per-user and dirty (`*.journal~`)
+    /// files, plus exported `.txt`/`.export` files. Entries are merged in
+    /// time order with duplicates across files dropped.
     pub fn read_entries(&self, filter: &JournalFilter) -> Result<Vec<JournalEntry>> {
-        let journal_dir = self.analyzer.journal_dir();
+        let entries = self.load_entries()?;
 
-        if !journal_dir.exists() {
-            return Ok(Vec::new());
+        let boot = match &filter.boot {
+            Some(boot) => Some(resolve_boot(&list_boots(&entries), boot)?),
+            None => None,
+        };
+
+        let mut entries: Vec<JournalEntry> = entries
+            .into_iter()
+            .filter(|entry| boot.is_none() || entry.boot_id() == boot.as_deref())
+            .filter(|entry| self.matches_filter(entry, filter))
+            .collect();
+
+        // Apply limit if specified
+        if let Some(limit) = filter.limit {
+            entries.truncate(limit);
         }
 
-        let mut entries = Vec::new();
+        Ok(entries)
+    }
 
-        // Read journal files (simplified - reads exported text journals)
-        // In production, this would parse binary .journal files
-        for entry in fs::read_dir(&journal_dir)
-            .with_context(|| format!("Failed to read journal directory: {}", journal_dir.display()))?
-        {
-            let entry = entry?;
-            let path = entry.path();
+    /// Boots found in the journals, oldest first
+    pub fn list_boots(&self) -> Result<Vec<BootInfo>> {
+        Ok(list_boots(&self.load_entries()?))
+    }
 
-            // Skip if not a readable file
-            if !path.is_file() {
+    /// Check every binary journal file: state, sealing and readability
+    pub fn verify(&self) -> Result<Vec<JournalFileReport>> {
+        let mut reports = Vec::new();
+        for path in self.journal_files()? {
+            if is_exported(&path) {
                 continue;
             }
-
-            // For now, look for exported journal files (.txt or .export)
-            if let Some(ext) = path.extension() {
-                if ext == "txt" || ext == "export" {
-                    if let Ok(file_entries) = self.parse_exported_journal(&path, filter) {
-                        entries.extend(file_entries);
+            let report = match JournalFile::open(&path) {
+                Ok(file) => {
+                    let scan = file.scan();
+                    JournalFileReport {
+                        path,
+                        header: Some(file.header),
+                        entries: scan.entries.len(),
+                        seal: scan.seal,
+                        undecoded_fields: scan.undecoded_fields,
+                        truncated: scan.truncated,
+                        problems: scan.problems,
                     }
                 }
+                Err(e) => JournalFileReport {
+                    path,
+                    header: None,
+                    entries: 0,
+                    seal: SealStatus::default(),
+                    undecoded_fields: 0,
+                    truncated: false,
+                    problems: vec![format!("{:#}", e)],
+                },
+            };
+            reports.push(report);
+        }
+        Ok(reports)
+    }
+
+    /// Journal files under the journal directory, machine-ID and
+    /// namespace subdirectories included
+    fn journal_files(&self) -> Result<Vec<PathBuf>> {
+        let mut files = Vec::new();
+        let mut dirs = vec![self.analyzer.journal_dir()];
+        while let Some(dir) = dirs.pop() {
+            if !dir.is_dir() {
+                continue;
+            }
+            for entry in fs::read_dir(&dir)
+                .with_context(|| format!("Failed to read journal directory: {}", dir.display()))?
+            {
+                let path = entry?.path();
+                let name = path.file_name().unwrap_or_default().to_string_lossy();
+                if path.is_dir() {
+                    dirs.push(path);
+                } else if is_journal_file(&name) || is_exported(&path) {
+                    files.push(path);
+                }
             }
         }
+        files.sort();
+        Ok(files)
+    }
 
-        // Apply limit if specified
-        if let Some(limit) = filter.limit {
-            entries.truncate(limit);
+    /// Every entry of every journal file, oldest first
+    fn load_entries(&self) -> Result<Vec<JournalEntry>> {
+        let mut entries = Vec::new();
+        let mut seen = HashSet::new();
+
+        for path in self.journal_files()? {
+            if is_exported(&path) {
+                if let Ok(file_entries) = self.parse_exported_journal(&path) {
+                    entries.extend(file_entries);
+                }
+                continue;
+            }
+
+            // Dirty and foreign files are expected in a guest's journal
+            // directory; what cannot be read is skipped
+            let file = match JournalFile::open(&path) {
+                Ok(file) => file,
+                Err(e) => {
+                    log::warn!("Skipping journal file: {:#}", e);
+                    continue;
+                }
+            };
+            let seqnum_id = file.header.seqnum_id.clone();
+            for raw in file.scan().entries {
+                // Rotation and `*.journal~` copies can hold the same entry
+                if seen.insert((seqnum_id.clone(), raw.seqnum, raw.xor_hash)) {
+                    entries.push(self.build_entry_from_raw(raw));
+                }
+            }
         }
 
+        entries.sort_by_key(|entry| entry.timestamp);
         Ok(entries)
     }
 
+    /// Build journal entry from a binary journal entry, with the
+    /// address fields `journalctl -o export` adds
+    fn build_entry_from_raw(&self, raw: RawEntry) -> JournalEntry {
+        let mut fields: HashMap<String, String> = raw
+            .fields
+            .into_iter()
+            .map(|(name, value)| (name, String::from_utf8_lossy(&value).into_owned()))
+            .collect();
+        fields.insert("__REALTIME_TIMESTAMP".to_string(), raw.realtime.to_string());
+        fields.insert(
+            "__MONOTONIC_TIMESTAMP".to_string(),
+            raw.monotonic.to_string(),
+        );
+        fields.insert("__SEQNUM".to_string(), raw.seqnum.to_string());
+        fields.entry("_BOOT_ID".to_string()).or_insert(raw.boot_id);
+        self.build_entry_from_fields(&fields)
+    }
+
     /// Parse exported journal file (text format)
-    fn parse_exported_journal(&self, path: &Path, filter: &JournalFilter) -> Result<Vec<JournalEntry>> {
+    fn parse_exported_journal(&self, path: &Path) -> Result<Vec<JournalEntry>> {
         let content = fs::read_to_string(path)
             .with_context(|| format!("Failed to read journal file: {}", path.display()))?;
 
@@ -142,9 +344,7 @@ impl JournalReader {
             if line.is_empty() {
                 // Entry separator
                 if let Some(entry) = current_entry.take() {
-                    if self.matches_filter(&entry, filter) {
-                        entries.push(entry);
-                    }
+                    entries.push(entry);
                 }
                 current_fields.clear();
                 continue;
@@ -164,9 +364,7 @@ impl JournalReader {
 
         // Don't forget the last entry
         if let Some(entry) = current_entry {
-            if self.matches_filter(&entry, filter) {
-                entries.push(entry);
-            }
+            entries.push(entry);
         }
 
         Ok(entries)
@@ -325,4 +523,77 @@ mod tests {
         // Just verify it was created successfully
         assert!(true);
     }
+
+    fn boot_entry(timestamp: u64, boot_id: &str) -> JournalEntry {
+        JournalEntry {
+            timestamp,
+            priority: 6,
+            unit: None,
+            message: "Boot".to_string(),
+            pid: None,
+            fields: HashMap::from([("_BOOT_ID".to_string(), boot_id.to_string())]),
+        }
+    }
+
+    #[test]
+    fn test_list_boots() {
+        let entries = vec![
+            boot_entry(10, "aaaa1111"),
+            boot_entry(20, "aaaa1111"),
+            boot_entry(30, "aaaa2222"),
+            boot_entry(40, "bbbb3333"),
+            boot_entry(50, "bbbb3333"),
+        ];
+        let boots = list_boots(&entries);
+        assert_eq!(boots.len(), 3);
+        assert_eq!(boots[0].boot_id, "aaaa1111");
+        assert_eq!(boots[0].offset, -2);
+        assert_eq!(
+            (boots[0].first, boots[0].last, boots[0].entries),
+            (10, 20, 2)
+        );
+        assert_eq!(boots[2].offset, 0);
+
+        assert_eq!(resolve_boot(&boots, "0").unwrap(), "bbbb3333");
+        assert_eq!(resolve_boot(&boots, "-1").unwrap(), "aaaa2222");
+        assert_eq!(resolve_boot(&boots, "1").unwrap(), "aaaa1111");
+        assert_eq!(resolve_boot(&boots, "BBBB").unwrap(), "bbbb3333");
+        assert!(resolve_boot(&boots, "-3").is_err());
+        assert!(resolve_boot(&boots, "aaaa").is_err());
+        assert!(resolve_boot(&boots, "cccc").is_err());
+    }
+
+    #[test]
+    fn test_read_entries_walks_machine_dirs() {
+        let root = tempfile::tempdir().unwrap();
+        let dir = root
+            .path()
+            .join("var/log/journal/0123456789abcdef0123456789abcdef");
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(
+            dir.join("system.export"),
+            "__REALTIME_TIMESTAMP=2\n_BOOT_ID=b2\nPRIORITY=3\nMESSAGE=second\n\n\
+             __REALTIME_TIMESTAMP=1\n_BOOT_ID=b1\nPRIORITY=6\nMESSAGE=first\n",
+        )
+        .unwrap();
+        // Not a journal; skipped rather than failing the read
+        fs::write(dir.join("system@0-1-2.journal~"), b"garbage").unwrap();
+
+        let reader = JournalReader::new(SystemdAnalyzer::new(root.path()));
+        let entries = reader.read_entries(&JournalFilter::default()).unwrap();
+        let messages: Vec<&str> = entries.iter().map(|e| e.message.as_str()).collect();
+        assert_eq!(messages, ["first", "second"]);
+
+        let filter = JournalFilter {
+            boot: Some("-1".to_string()),
+            ..Default::default()
+        };
+        let entries = reader.read_entries(&filter).unwrap();
+        assert_eq!(entries.len(), 1);
+        assert_eq!(entries[0].boot_id(), Some("b1"));
+
+        let reports = reader.verify().unwrap();
+        assert_eq!(reports.len(), 1);
+        assert!(reports[0].header.is_none());
+    }
 }
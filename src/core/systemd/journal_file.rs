@@ -0,0 +1,607 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Binary journal file parsing
+//!
+//! Reads `.journal` files the way journald leaves them on disk: regular and
+//! compact layouts, LZ4- and ZSTD-compressed fields, and the TAG objects of
+//! Forward Secure Sealing. Objects are walked in file order rather than
+//! through the hash tables and entry arrays, so files journald was still
+//! writing to (`system.journal` of a crashed guest, `*.journal~`) give up
+//! every entry before the torn tail.
+//!
+//! The tags can only be checked for order here; checking their HMACs needs
+//! the verification key and is left to `journalctl --verify-key`.
+
+use anyhow::{bail, Context, Result};
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::process::Command;
+
+/// File signature, "LPKSHHRH"
+pub const SIGNATURE: &[u8; 8] = b"LPKSHHRH";
+
+/// Compatible flag: the file carries FSS tags
+pub const COMPATIBLE_SEALED: u32 = 1 << 0;
+
+pub const INCOMPATIBLE_COMPRESSED_XZ: u32 = 1 << 0;
+pub const INCOMPATIBLE_COMPRESSED_LZ4: u32 = 1 << 1;
+pub const INCOMPATIBLE_KEYED_HASH: u32 = 1 << 2;
+pub const INCOMPATIBLE_COMPRESSED_ZSTD: u32 = 1 << 3;
+pub const INCOMPATIBLE_COMPACT: u32 = 1 << 4;
+
+const OBJECT_DATA: u8 = 1;
+const OBJECT_ENTRY: u8 = 3;
+const OBJECT_TAG: u8 = 7;
+
+const OBJECT_COMPRESSED_XZ: u8 = 1 << 0;
+const OBJECT_COMPRESSED_LZ4: u8 = 1 << 1;
+const OBJECT_COMPRESSED_ZSTD: u8 = 1 << 2;
+
+/// Size of the object header, and of the smallest header journald writes
+const OBJECT_HEADER_SIZE: usize = 16;
+const MIN_HEADER_SIZE: usize = 208;
+
+/// Largest field accepted from a compressed object
+const MAX_FIELD_SIZE: usize = 768 * 1024 * 1024;
+
+/// How journald left the file
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileState {
+    /// Closed cleanly
+    Offline,
+    /// Open for writing; the tail may be torn
+    Online,
+    /// Rotated away
+    Archived,
+    Unknown(u8),
+}
+
+impl std::fmt::Display for FileState {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileState::Offline => write!(f, "offline"),
+            FileState::Online => write!(f, "online"),
+            FileState::Archived => write!(f, "archived"),
+            FileState::Unknown(state) => write!(f, "unknown ({})", state),
+        }
+    }
+}
+
+/// Journal file header
+#[derive(Debug, Clone)]
+pub struct JournalHeader {
+    pub compatible_flags: u32,
+    pub incompatible_flags: u32,
+    pub state: FileState,
+    pub file_id: String,
+    pub machine_id: String,
+    /// Boot of the last entry written
+    pub tail_boot_id: String,
+    /// Entries of files with the same sequence number ID share one counter
+    pub seqnum_id: String,
+    pub header_size: u64,
+    pub arena_size: u64,
+    pub tail_object_offset: u64,
+    pub n_entries: u64,
+    pub head_seqnum: u64,
+    pub tail_seqnum: u64,
+    /// Microseconds since the epoch
+    pub head_realtime: u64,
+    pub tail_realtime: u64,
+}
+
+impl JournalHeader {
+    pub fn is_sealed(&self) -> bool {
+        self.compatible_flags & COMPATIBLE_SEALED != 0
+    }
+
+    pub fn is_compact(&self) -> bool {
+        self.incompatible_flags & INCOMPATIBLE_COMPACT != 0
+    }
+
+    /// Names of the incompatible flags set, as `journalctl --header` shows them
+    pub fn features(&self) -> Vec<&'static str> {
+        [
+            (INCOMPATIBLE_COMPRESSED_XZ, "COMPRESSED-XZ"),
+            (INCOMPATIBLE_COMPRESSED_LZ4, "COMPRESSED-LZ4"),
+            (INCOMPATIBLE_KEYED_HASH, "KEYED-HASH"),
+            (INCOMPATIBLE_COMPRESSED_ZSTD, "COMPRESSED-ZSTD"),
+            (INCOMPATIBLE_COMPACT, "COMPACT"),
+        ]
+        .into_iter()
+        .filter(|(flag, _)| self.incompatible_flags & flag != 0)
+        .map(|(_, name)| name)
+        .collect()
+    }
+}
+
+/// An entry as stored, before any interpretation of its fields
+#[derive(Debug, Clone)]
+pub struct RawEntry {
+    pub seqnum: u64,
+    /// Microseconds since the epoch
+    pub realtime: u64,
+    /// Microseconds since boot
+    pub monotonic: u64,
+    pub boot_id: String,
+    pub xor_hash: u64,
+    /// `FIELD=value` pairs in stored order; values may be binary
+    pub fields: Vec<(String, Vec<u8>)>,
+}
+
+/// What the TAG objects of a file say
+#[derive(Debug, Clone, Default)]
+pub struct SealStatus {
+    pub sealed: bool,
+    pub tags: u64,
+    /// FSS epoch of the last tag
+    pub last_epoch: Option<u64>,
+    /// Entries covered by a tag
+    pub sealed_entries: usize,
+    /// Entries after the last tag, which anyone could have appended
+    pub unsealed_entries: usize,
+}
+
+/// Everything read from one file
+#[derive(Debug, Clone, Default)]
+pub struct JournalScan {
+    pub entries: Vec<RawEntry>,
+    pub seal: SealStatus,
+    /// XZ-compressed or corrupt fields left out of their entries
+    pub undecoded_fields: usize,
+    /// The object walk stopped before the end of the arena
+    pub truncated: bool,
+    /// Ordering problems found on the way
+    pub problems: Vec<String>,
+}
+
+/// A `.journal` file read into memory
+pub struct JournalFile {
+    pub path: PathBuf,
+    pub header: JournalHeader,
+    data: Vec<u8>,
+}
+
+fn le_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn le_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        data.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+/// 128-bit ID as journald prints it: 32 lowercase hex digits
+fn id128(data: &[u8], offset: usize) -> String {
+    data.get(offset..offset + 16)
+        .unwrap_or_default()
+        .iter()
+        .map(|b| format!("{:02x}", b))
+        .collect()
+}
+
+impl JournalFile {
+    pub fn open(path: &Path) -> Result<Self> {
+        let data = std::fs::read(path)
+            .with_context(|| format!("Failed to read journal file: {}", path.display()))?;
+        Self::parse(path, data).with_context(|| format!("Invalid journal file: {}", path.display()))
+    }
+
+    pub fn parse(path: &Path, data: Vec<u8>) -> Result<Self> {
+        if data.len() < MIN_HEADER_SIZE || &data[..8] != SIGNATURE {
+            bail!("Not a journal file");
+        }
+        let u64_at = |offset| le_u64(&data, offset).unwrap_or(0);
+
+        let incompatible_flags = le_u32(&data, 12).unwrap_or(0);
+        let header = JournalHeader {
+            compatible_flags: le_u32(&data, 8).unwrap_or(0),
+            incompatible_flags,
+            state: match data[16] {
+                0 => FileState::Offline,
+                1 => FileState::Online,
+                2 => FileState::Archived,
+                state => FileState::Unknown(state),
+            },
+            file_id: id128(&data, 24),
+            machine_id: id128(&data, 40),
+            tail_boot_id: id128(&data, 56),
+            seqnum_id: id128(&data, 72),
+            header_size: u64_at(88),
+            arena_size: u64_at(96),
+            tail_object_offset: u64_at(136),
+            n_entries: u64_at(152),
+            tail_seqnum: u64_at(160),
+            head_seqnum: u64_at(168),
+            head_realtime: u64_at(184),
+            tail_realtime: u64_at(192),
+        };
+
+        let unknown = incompatible_flags
+            & !(INCOMPATIBLE_COMPRESSED_XZ
+                | INCOMPATIBLE_COMPRESSED_LZ4
+                | INCOMPATIBLE_KEYED_HASH
+                | INCOMPATIBLE_COMPRESSED_ZSTD
+                | INCOMPATIBLE_COMPACT);
+        if unknown != 0 {
+            bail!("Unsupported incompatible flags {:#x}", unknown);
+        }
+        if (header.header_size as usize) < MIN_HEADER_SIZE
+            || header.header_size as usize > data.len()
+        {
+            bail!("Bad header size {}", header.header_size);
+        }
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            header,
+            data,
+        })
+    }
+
+    /// Walk the objects of the file, collecting entries and tags
+    pub fn scan(&self) -> JournalScan {
+        let data = &self.data;
+        let compact = self.header.is_compact();
+        let end = (self
+            .header
+            .header_size
+            .saturating_add(self.header.arena_size) as usize)
+            .min(data.len());
+        let tail = self.header.tail_object_offset as usize;
+
+        let mut scan = JournalScan {
+            seal: SealStatus {
+                sealed: self.header.is_sealed(),
+                ..SealStatus::default()
+            },
+            ..JournalScan::default()
+        };
+        let mut fields: HashMap<usize, Option<(String, Vec<u8>)>> = HashMap::new();
+        let mut pending = 0;
+        let mut last_seqnum = 0;
+
+        let mut offset = self.header.header_size as usize;
+        while offset + OBJECT_HEADER_SIZE <= end && (tail == 0 || offset <= tail) {
+            let kind = data[offset];
+            let size = le_u64(data, offset + 8).unwrap_or(0) as usize;
+            if kind == 0 && size == 0 {
+                break;
+            }
+            if size < OBJECT_HEADER_SIZE || size > end - offset {
+                scan.truncated = true;
+                break;
+            }
+            let object = &data[offset..offset + size];
+
+            match kind {
+                OBJECT_ENTRY if size >= 64 => {
+                    let seqnum = le_u64(object, 16).unwrap_or(0);
+                    if seqnum <= last_seqnum {
+                        scan.problems.push(format!(
+                            "Entry at {:#x}: sequence number {} after {}",
+                            offset, seqnum, last_seqnum
+                        ));
+                    }
+                    last_seqnum = seqnum;
+
+                    let item_size = if compact { 4 } else { 16 };
+                    let mut entry = RawEntry {
+                        seqnum,
+                        realtime: le_u64(object, 24).unwrap_or(0),
+                        monotonic: le_u64(object, 32).unwrap_or(0),
+                        boot_id: id128(object, 40),
+                        xor_hash: le_u64(object, 56).unwrap_or(0),
+                        fields: Vec::new(),
+                    };
+                    for item in object[64..].chunks_exact(item_size) {
+                        let data_offset = if compact {
+                            le_u32(item, 0).unwrap_or(0) as usize
+                        } else {
+                            le_u64(item, 0).unwrap_or(0) as usize
+                        };
+                        let field = fields
+                            .entry(data_offset)
+                            .or_insert_with(|| self.field(data_offset));
+                        match field {
+                            Some(field) => entry.fields.push(field.clone()),
+                            None => scan.undecoded_fields += 1,
+                        }
+                    }
+                    scan.entries.push(entry);
+                    pending += 1;
+                }
+                OBJECT_TAG if size >= 64 => {
+                    let seqnum = le_u64(object, 16).unwrap_or(0);
+                    let epoch = le_u64(object, 24).unwrap_or(0);
+                    if seqnum != scan.seal.tags + 1 {
+                        scan.problems.push(format!(
+                            "Tag at {:#x}: sequence number {}, expected {}",
+                            offset,
+                            seqnum,
+                            scan.seal.tags + 1
+                        ));
+                    }
+                    if scan.seal.last_epoch.is_some_and(|last| epoch < last) {
+                        scan.problems
+                            .push(format!("Tag at {:#x}: epoch {} goes back", offset, epoch));
+                    }
+                    scan.seal.tags += 1;
+                    scan.seal.last_epoch = Some(epoch);
+                    scan.seal.sealed_entries += pending;
+                    pending = 0;
+                }
+                _ => {}
+            }
+
+            offset += size.next_multiple_of(8);
+        }
+
+        scan.seal.unsealed_entries = pending;
+        if scan.seal.sealed && scan.seal.tags == 0 {
+            scan.problems
+                .push("File is marked sealed but has no tags".to_string());
+        }
+        scan
+    }
+
+    /// The `FIELD=value` of the DATA object at `offset`
+    fn field(&self, offset: usize) -> Option<(String, Vec<u8>)> {
+        let data = &self.data;
+        if data.get(offset) != Some(&OBJECT_DATA) {
+            return None;
+        }
+        let flags = *data.get(offset + 1)?;
+        let size = le_u64(data, offset + 8)? as usize;
+        let start = offset + if self.header.is_compact() { 72 } else { 64 };
+        let payload = data.get(start..offset.checked_add(size)?)?;
+
+        let payload = if flags & OBJECT_COMPRESSED_ZSTD != 0 {
+            let mut decoded = Vec::new();
+            let mut decoder = zstd::stream::read::Decoder::new(payload).ok()?;
+            std::io::Read::read_to_end(
+                &mut std::io::Read::take(&mut decoder, MAX_FIELD_SIZE as u64),
+                &mut decoded,
+            )
+            .ok()?;
+            decoded
+        } else if flags & OBJECT_COMPRESSED_LZ4 != 0 {
+            let size = le_u64(payload, 0)? as usize;
+            if size > MAX_FIELD_SIZE {
+                return None;
+            }
+            lz4_block(&payload[8..], size)?
+        } else if flags & OBJECT_COMPRESSED_XZ != 0 {
+            return None;
+        } else {
+            payload.to_vec()
+        };
+
+        let split = payload.iter().position(|b| *b == b'=')?;
+        let name = std::str::from_utf8(&payload[..split]).ok()?.to_string();
+        Some((name, payload[split + 1..].to_vec()))
+    }
+}
+
+/// Decode an LZ4 block of known decompressed size
+fn lz4_block(src: &[u8], size: usize) -> Option<Vec<u8>> {
+    fn length(src: &[u8], i: &mut usize, mut len: usize) -> Option<usize> {
+        if len == 15 {
+            loop {
+                let b = *src.get(*i)?;
+                *i += 1;
+                len += b as usize;
+                if b != 255 {
+                    break;
+                }
+            }
+        }
+        Some(len)
+    }
+
+    let mut out = Vec::with_capacity(size);
+    let mut i = 0;
+    while i < src.len() {
+        let token = src[i];
+        i += 1;
+        let literals = length(src, &mut i, (token >> 4) as usize)?;
+        out.extend_from_slice(src.get(i..i.checked_add(literals)?)?);
+        i += literals;
+        if i == src.len() {
+            break;
+        }
+
+        let distance = u16::from_le_bytes([*src.get(i)?, *src.get(i + 1)?]) as usize;
+        i += 2;
+        if distance == 0 || distance > out.len() {
+            return None;
+        }
+        let matched = length(src, &mut i, (token & 15) as usize)? + 4;
+        if out.len() + matched > size {
+            return None;
+        }
+        let start = out.len() - distance;
+        for k in 0..matched {
+            out.push(out[start + k]);
+        }
+    }
+    (out.len() == size).then_some(out)
+}
+
+/// Whether `name` is a journal file journald wrote, active, rotated or
+/// left behind dirty (`*.journal~`)
+pub fn is_journal_file(name: &str) -> bool {
+    name.ends_with(".journal") || name.ends_with(".journal~")
+}
+
+/// Check the seals of `path` with an FSS verification key, as printed by
+/// `journalctl --setup-keys`. Needs `journalctl` on the host.
+///
+/// Returns whether the file passed and what journalctl said.
+pub fn verify_with_key(path: &Path, key: &str) -> Result<(bool, String)> {
+    let output = Command::new("journalctl")
+        .arg("--file")
+        .arg(path)
+        .arg("--verify")
+        .arg(format!("--verify-key={}", key))
+        .output()
+        .context("Failed to execute journalctl")?;
+    let text = String::from_utf8_lossy(&output.stderr).trim().to_string();
+    Ok((output.status.success(), text))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// A file journald could have written: one DATA object per field,
+    /// entries pointing at them, and a tag after the first `sealed` entries
+    fn journal(compact: bool, sealed: usize, entries: &[&[&[u8]]]) -> Vec<u8> {
+        fn object(out: &mut Vec<u8>, kind: u8, flags: u8, body: &[u8]) -> usize {
+            let offset = out.len();
+            out.push(kind);
+            out.push(flags);
+            out.extend_from_slice(&[0; 6]);
+            out.extend_from_slice(&((16 + body.len()) as u64).to_le_bytes());
+            out.extend_from_slice(body);
+            out.resize(out.len().next_multiple_of(8), 0);
+            offset
+        }
+
+        let mut out = vec![0u8; 272];
+        out[..8].copy_from_slice(SIGNATURE);
+        out[8..12].copy_from_slice(&(if sealed > 0 { 1u32 } else { 0 }).to_le_bytes());
+        out[12..16].copy_from_slice(&(if compact { 0x18u32 } else { 0x8 }).to_le_bytes());
+        out[16] = 2;
+        out[88..96].copy_from_slice(&272u64.to_le_bytes());
+
+        let mut tail = 0;
+        for (n, fields) in entries.iter().enumerate() {
+            let mut items = Vec::new();
+            for field in *fields {
+                let mut body = vec![0u8; if compact { 56 } else { 48 }];
+                let flags = if field.starts_with(b"LZ4") {
+                    // "LZ4=aaaaaaaaaaaaaaaa" as a literal and a match
+                    body.extend_from_slice(&20u64.to_le_bytes());
+                    body.extend_from_slice(&[0x5b, b'L', b'Z', b'4', b'=', b'a', 1, 0]);
+                    OBJECT_COMPRESSED_LZ4
+                } else if field.starts_with(b"ZSTD") {
+                    body.extend_from_slice(&zstd::encode_all(*field, 3).unwrap());
+                    OBJECT_COMPRESSED_ZSTD
+                } else {
+                    body.extend_from_slice(field);
+                    0
+                };
+                let offset = object(&mut out, OBJECT_DATA, flags, &body);
+                if compact {
+                    items.extend_from_slice(&(offset as u32).to_le_bytes());
+                } else {
+                    items.extend_from_slice(&(offset as u64).to_le_bytes());
+                    items.extend_from_slice(&[0; 8]);
+                }
+            }
+
+            let mut body = Vec::new();
+            body.extend_from_slice(&(n as u64 + 1).to_le_bytes());
+            body.extend_from_slice(&(1_700_000_000_000_000 + n as u64).to_le_bytes());
+            body.extend_from_slice(&(n as u64).to_le_bytes());
+            body.extend_from_slice(&[0xab; 16]);
+            body.extend_from_slice(&[0; 8]);
+            body.extend_from_slice(&items);
+            tail = object(&mut out, OBJECT_ENTRY, 0, &body);
+
+            if n + 1 == sealed {
+                let mut body = 1u64.to_le_bytes().to_vec();
+                body.extend_from_slice(&[0; 40]);
+                tail = object(&mut out, OBJECT_TAG, 0, &body);
+            }
+        }
+
+        let arena = (out.len() - 272) as u64;
+        out[96..104].copy_from_slice(&arena.to_le_bytes());
+        out[136..144].copy_from_slice(&(tail as u64).to_le_bytes());
+        out[152..160].copy_from_slice(&(entries.len() as u64).to_le_bytes());
+        out
+    }
+
+    #[test]
+    fn test_scan() {
+        for compact in [false, true] {
+            let data = journal(
+                compact,
+                1,
+                &[
+                    &[b"MESSAGE=hello", b"PRIORITY=3", b"LZ4"],
+                    &[b"ZSTD=compressed", b"PRIORITY=3"],
+                ],
+            );
+            let file = JournalFile::parse(Path::new("system.journal"), data).unwrap();
+            assert_eq!(file.header.state, FileState::Archived);
+            assert_eq!(file.header.is_compact(), compact);
+
+            let scan = file.scan();
+            assert!(!scan.truncated);
+            assert_eq!(scan.undecoded_fields, 0);
+            assert!(scan.problems.is_empty(), "{:?}", scan.problems);
+            assert_eq!(scan.entries.len(), 2);
+            assert_eq!(scan.entries[0].boot_id, "ab".repeat(16));
+            assert_eq!(
+                scan.entries[0].fields,
+                vec![
+                    ("MESSAGE".to_string(), b"hello".to_vec()),
+                    ("PRIORITY".to_string(), b"3".to_vec()),
+                    ("LZ4".to_string(), b"a".repeat(16)),
+                ]
+            );
+            assert_eq!(
+                scan.entries[1].fields[0],
+                ("ZSTD".to_string(), b"compressed".to_vec())
+            );
+
+            assert!(scan.seal.sealed);
+            assert_eq!(scan.seal.tags, 1);
+            assert_eq!(scan.seal.sealed_entries, 1);
+            assert_eq!(scan.seal.unsealed_entries, 1);
+        }
+    }
+
+    #[test]
+    fn test_scan_torn_tail() {
+        let mut data = journal(false, 0, &[&[b"MESSAGE=one"], &[b"MESSAGE=two"]]);
+        // journald died while appending the second entry
+        data.truncate(data.len() - 8);
+        let file = JournalFile::parse(Path::new("system.journal~"), data).unwrap();
+        let scan = file.scan();
+        assert!(scan.truncated);
+        assert_eq!(scan.entries.len(), 1);
+    }
+
+    #[test]
+    fn test_field_at_end_of_file() {
+        let mut data = journal(false, 0, &[]);
+        data.push(OBJECT_DATA);
+        let offset = data.len() - 1;
+        let file = JournalFile::parse(Path::new("system.journal"), data).unwrap();
+        assert!(file.field(offset).is_none());
+    }
+
+    #[test]
+    fn test_parse_rejects_other_files() {
+        assert!(JournalFile::parse(Path::new("x"), vec![0; 4096]).is_err());
+        let mut data = journal(false, 0, &[]);
+        data[12] |= 0x80;
+        assert!(JournalFile::parse(Path::new("x"), data).is_err());
+    }
+
+    #[test]
+    fn test_lz4_block() {
+        assert_eq!(lz4_block(&[0x30, b'a', b'b', b'c'], 3).unwrap(), b"abc");
+        assert_eq!(
+            lz4_block(&[0x1f, b'x', 1, 0, 0x01, 0x00], 21).unwrap(),
+            b"x".repeat(21)
+        );
+        assert!(lz4_block(&[0x1f, b'x', 2, 0, 0x00], 20).is_none());
+    }
+}
@@ -89,4 +89,8 @@ pub struct ConversionResult {
     pub duration_secs: f64,
     pub success: bool,
     pub error: Option<String>,
+    /// Block-by-block comparison of the output with the source, when
+    /// verification was asked for
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub verification: Option<crate::converters::VerificationReport>,
 }
\ No newline at end of file
@@ -4,9 +4,13 @@
 //! This module implements guest OS detection without external dependencies
 
 use crate::core::{Firmware, GuestIdentity, GuestType, Result};
-use crate::disk::{DiskReader, FileSystem, PartitionTable};
+use crate::disk::{
+    Apfs, DiskReader, Ext4, FileSystem, FileSystemType, HfsPlus, PartitionTable, Xfs,
+};
 use std::path::Path;
 
+const SYSTEM_VERSION: &str = "/System/Library/CoreServices/SystemVersion.plist";
+
 /// Guest OS detector
 pub struct GuestDetector {}
 
@@ -30,7 +34,8 @@ impl GuestDetector {
     /// ```
     pub fn detect_from_image<P: AsRef<Path>>(&self, path: P) -> Result<GuestIdentity> {
         // Open disk image
-        let mut reader = DiskReader::open(path.as_ref())?;
+        let path = path.as_ref();
+        let mut reader = DiskReader::open(path)?;
 
         // Parse partition table
         let partition_table = PartitionTable::parse(&mut reader)?;
@@ -42,6 +47,7 @@ impl GuestDetector {
         let architecture = String::from("x86_64");
         let mut firmware = Firmware::Bios;
         let mut distro = None;
+        let mut found_os_release = false;
 
         // Check for GPT (indicates UEFI)
         if matches!(
@@ -54,6 +60,20 @@ impl GuestDetector {
         // Examine each partition
         for partition in partition_table.partitions() {
             if let Ok(fs) = FileSystem::detect(&mut reader, partition) {
+                // Once a Linux filesystem was seen, only its root is still
+                // looked for
+                if os_type == GuestType::Linux
+                    && matches!(
+                        fs.fs_type(),
+                        FileSystemType::Ntfs
+                            | FileSystemType::Ufs
+                            | FileSystemType::HfsPlus
+                            | FileSystemType::Apfs
+                    )
+                {
+                    continue;
+                }
+
                 // Detect OS based on filesystem and partition analysis
                 match fs.fs_type() {
                     crate::disk::FileSystemType::Ntfs => {
@@ -66,6 +86,14 @@ impl GuestDetector {
                     | crate::disk::FileSystemType::Xfs
                     | crate::disk::FileSystemType::Btrfs
                     | crate::disk::FileSystemType::Zfs => {
+                        // /boot and data filesystems have no os-release; keep
+                        // looking for the root one, and the guess made so far
+                        let offset = partition.start_lba * partition_table.sector_size();
+                        let os_release = read_os_release(path, fs.fs_type(), offset);
+                        if os_release.is_none() && os_type == GuestType::Linux {
+                            continue;
+                        }
+
                         // Linux or BSD (ZFS can be either)
                         // Default to Linux unless BSD hints appear
                         os_type = GuestType::Linux;
@@ -137,6 +165,18 @@ impl GuestDetector {
                         }
 
                         os_version = "Unknown".to_string();
+
+                        if let Some(content) = os_release {
+                            for (key, value) in os_release_fields(&content) {
+                                match key {
+                                    "NAME" => os_name = value,
+                                    "VERSION_ID" => os_version = value,
+                                    "ID" => distro = Some(value),
+                                    _ => {}
+                                }
+                            }
+                            found_os_release = true;
+                        }
                     }
 
                     // BSD detection (UFS or ZFS)
@@ -162,16 +202,26 @@ impl GuestDetector {
                         os_version = "Unknown".to_string();
                     }
 
-                    crate::disk::FileSystemType::HfsPlus => {
+                    crate::disk::FileSystemType::HfsPlus
+                    | crate::disk::FileSystemType::Apfs => {
                         os_type = GuestType::MacOS;
-                        os_name = "macOS (HFS+)".to_string();
+                        os_name = if fs.fs_type() == &FileSystemType::Apfs {
+                            "macOS (APFS)".to_string()
+                        } else {
+                            "macOS (HFS+)".to_string()
+                        };
                         os_version = "Unknown".to_string();
-                    }
 
-                    crate::disk::FileSystemType::Apfs => {
-                        os_type = GuestType::MacOS;
-                        os_name = "macOS (APFS)".to_string();
-                        os_version = "Unknown".to_string();
+                        let offset = partition.start_lba * partition_table.sector_size();
+                        if let Some(content) = read_system_version(path, fs.fs_type(), offset) {
+                            for (key, value) in plist_strings(&content) {
+                                match key.as_str() {
+                                    "ProductName" => os_name = value,
+                                    "ProductVersion" => os_version = value,
+                                    _ => {}
+                                }
+                            }
+                        }
                     }
 
                     crate::disk::FileSystemType::Fat32 => {
@@ -183,8 +233,10 @@ impl GuestDetector {
                     _ => {}
                 }
 
-                // If we found an OS, break
-                if os_type != GuestType::Unknown {
+                // If we found an OS, break; Linux once its root is read
+                if os_type != GuestType::Unknown
+                    && (os_type != GuestType::Linux || found_os_release)
+                {
                     break;
                 }
             }
@@ -202,6 +254,94 @@ impl GuestDetector {
     }
 }
 
+/// /etc/os-release of the ext2/3/4 or XFS filesystem at `offset`, read
+/// without mounting
+fn read_os_release(path: &Path, fs_type: &FileSystemType, offset: u64) -> Option<String> {
+    let reader = DiskReader::open(path).ok()?;
+    let content = match fs_type {
+        FileSystemType::Ext => Ext4::from_reader(reader, offset)
+            .ok()?
+            .read_file("/etc/os-release"),
+        FileSystemType::Xfs => Xfs::from_reader(reader, offset)
+            .ok()?
+            .read_file("/etc/os-release"),
+        _ => return None,
+    };
+    content
+        .ok()
+        .map(|content| String::from_utf8_lossy(&content).into_owned())
+}
+
+/// SystemVersion.plist of the HFS+ volume or APFS container at `offset`,
+/// from the System volume of a container if it has one
+fn read_system_version(path: &Path, fs_type: &FileSystemType, offset: u64) -> Option<String> {
+    let reader = DiskReader::open(path).ok()?;
+    let content = match fs_type {
+        FileSystemType::HfsPlus => HfsPlus::from_reader(reader, offset)
+            .ok()?
+            .read_file(SYSTEM_VERSION)
+            .ok()?,
+        FileSystemType::Apfs => {
+            let apfs = Apfs::from_reader(reader, offset).ok()?;
+            let mut volumes = apfs.volumes().ok()?;
+            volumes.sort_by_key(|volume| volume.role.as_deref() != Some("System"));
+            volumes.iter().find_map(|volume| {
+                apfs.volume(volume.index)
+                    .ok()?
+                    .read_file(SYSTEM_VERSION)
+                    .ok()
+            })?
+        }
+        _ => return None,
+    };
+    Some(String::from_utf8_lossy(&content).into_owned())
+}
+
+/// Keys and string values of the dictionary of an XML property list
+fn plist_strings(content: &str) -> Vec<(String, String)> {
+    let options = roxmltree::ParsingOptions {
+        allow_dtd: true,
+        ..Default::default()
+    };
+    let Ok(doc) = roxmltree::Document::parse_with_options(content, options) else {
+        return Vec::new();
+    };
+    let Some(dict) = doc
+        .root_element()
+        .children()
+        .find(|node| node.has_tag_name("dict"))
+    else {
+        return Vec::new();
+    };
+
+    let mut fields = Vec::new();
+    let mut key = None;
+    for node in dict.children().filter(|node| node.is_element()) {
+        match node.tag_name().name() {
+            "key" => key = node.text(),
+            "string" => {
+                if let Some(key) = key.take() {
+                    fields.push((key.to_string(), node.text().unwrap_or("").to_string()));
+                }
+            }
+            _ => key = None,
+        }
+    }
+    fields
+}
+
+/// KEY=value pairs of an os-release file, unquoted
+fn os_release_fields(content: &str) -> impl Iterator<Item = (&str, String)> {
+    content.lines().filter_map(|line| {
+        let (key, value) = line.trim().split_once('=')?;
+        if key.starts_with('#') {
+            return None;
+        }
+        let value = value.trim().trim_matches(|c: char| c == '"' || c == '\'');
+        Some((key, value.to_string()))
+    })
+}
+
 impl Default for GuestDetector {
     fn default() -> Self {
         Self::new()
@@ -217,4 +357,43 @@ mod tests {
         let detector = GuestDetector::new();
         let _ = detector;
     }
+
+    #[test]
+    fn test_os_release_fields() {
+        let content = "NAME=\"Rocky Linux\"\n# comment=x\nVERSION_ID='9.4'\nID=rocky\n";
+        let fields: Vec<_> = os_release_fields(content).collect();
+        assert_eq!(
+            fields,
+            [
+                ("NAME", "Rocky Linux".to_string()),
+                ("VERSION_ID", "9.4".to_string()),
+                ("ID", "rocky".to_string()),
+            ]
+        );
+    }
+    #[test]
+    fn test_plist_strings() {
+        let content = r#"<?xml version="1.0" encoding="UTF-8"?>
+<!DOCTYPE plist PUBLIC "-//Apple//DTD PLIST 1.0//EN" "http://www.apple.com/DTDs/PropertyList-1.0.dtd">
+<plist version="1.0">
+<dict>
+	<key>ProductBuildVersion</key>
+	<string>23F79</string>
+	<key>ProductName</key>
+	<string>macOS</string>
+	<key>ProductVersion</key>
+	<string>14.5</string>
+</dict>
+</plist>
+"#;
+        assert_eq!(
+            plist_strings(content),
+            [
+                ("ProductBuildVersion".to_string(), "23F79".to_string()),
+                ("ProductName".to_string(), "macOS".to_string()),
+                ("ProductVersion".to_string(), "14.5".to_string()),
+            ]
+        );
+        assert!(plist_strings("bplist00").is_empty());
+    }
 }
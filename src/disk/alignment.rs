@@ -0,0 +1,565 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Partition alignment and sector size analysis
+//!
+//! Images partitioned by old installers start their first partition at
+//! sector 63. On storage with 4 KiB physical sectors every guest I/O then
+//! straddles two sectors and turns into a read-modify-write. Images
+//! partitioned with 4096-byte logical sectors (4Kn) have the opposite
+//! problem: their partition table is only found when the hypervisor
+//! presents 4096-byte sectors.
+//!
+//! [`AlignmentReport::analyze`] reports both, and
+//! [`AlignmentReport::realignment_plan`] works out where misaligned
+//! partitions have to move to start on a 1 MiB boundary.
+
+use crate::core::{Error, Result};
+use crate::disk::filesystem::{FileSystem, FileSystemType};
+use crate::disk::partition::{Partition, PartitionTable, PartitionType};
+use crate::disk::reader::DiskReader;
+use crate::lint::Severity;
+use serde::{Deserialize, Serialize};
+use std::fs::OpenOptions;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+/// Boundary partitions should start on, as parted and Windows use
+pub const ALIGNMENT: u64 = 1024 * 1024;
+/// Physical sector size of current disks, SSDs and most SAN LUNs
+pub const PHYSICAL_SECTOR_SIZE: u64 = 4096;
+/// Sector size QEMU presents unless told otherwise
+const DEFAULT_SECTOR_SIZE: u64 = 512;
+/// Backup GPT header and entries at the end of the disk
+pub(crate) const GPT_BACKUP_SIZE: u64 = 33 * 512;
+
+/// MBR extended partition types
+pub(crate) const MBR_EXTENDED: [u8; 3] = [0x05, 0x0f, 0x85];
+
+/// Alignment of one partition
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PartitionAlignment {
+    pub number: u32,
+    /// Start offset in bytes
+    pub start: u64,
+    /// Size in bytes
+    pub size: u64,
+    /// Starts on a 1 MiB boundary
+    pub aligned: bool,
+    /// Starts on a physical sector boundary
+    pub physically_aligned: bool,
+    /// MBR extended partition holding logical partitions
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub extended: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub filesystem: Option<String>,
+}
+
+/// A problem found by the analysis, with what to do about it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlignmentIssue {
+    pub severity: Severity,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub partition: Option<u32>,
+    pub message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<String>,
+}
+
+/// Partition geometry of a disk image
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AlignmentReport {
+    /// gpt, msdos or unknown
+    pub table_type: String,
+    /// Sector size the partition table was written for
+    pub table_sector_size: u64,
+    /// Sector sizes of the device; for image files, what QEMU presents and
+    /// the physical sector size of current storage
+    pub logical_sector_size: u64,
+    pub physical_sector_size: u64,
+    /// Sector sizes were read from a block device or a VHDX header
+    pub block_device: bool,
+    /// Disk size in bytes
+    pub disk_size: u64,
+    pub partitions: Vec<PartitionAlignment>,
+    pub issues: Vec<AlignmentIssue>,
+}
+
+/// Where a partition moves to during realignment
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PartitionMove {
+    pub number: u32,
+    /// Current start offset in bytes
+    pub from: u64,
+    /// New start offset in bytes
+    pub to: u64,
+    /// Size in bytes, unchanged
+    pub size: u64,
+}
+
+impl AlignmentReport {
+    /// Analyze the partition table of a disk image or block device
+    pub fn analyze(reader: &mut DiskReader) -> Result<Self> {
+        let device_sizes = reader.sector_sizes();
+        let (logical, physical) = match device_sizes {
+            Some((logical, physical)) => (u64::from(logical), u64::from(physical)),
+            None => (DEFAULT_SECTOR_SIZE, PHYSICAL_SECTOR_SIZE),
+        };
+
+        let table_sector_size = detect_table_sector_size(reader)?;
+        let table = PartitionTable::parse_with_sector_size(reader, table_sector_size)?;
+        let table_type = match table.table_type() {
+            PartitionType::MBR => "msdos",
+            PartitionType::GPT => "gpt",
+            PartitionType::Unknown => "unknown",
+        };
+
+        let physical_boundary = physical.max(PHYSICAL_SECTOR_SIZE);
+        let mut report = Self {
+            table_type: table_type.to_string(),
+            table_sector_size,
+            logical_sector_size: logical,
+            physical_sector_size: physical,
+            block_device: device_sizes.is_some(),
+            disk_size: reader.size(),
+            partitions: Vec::new(),
+            issues: Vec::new(),
+        };
+
+        for partition in table.partitions() {
+            let start = partition.start_lba * table_sector_size;
+            let size = partition.size_sectors * table_sector_size;
+            let filesystem = if table_sector_size == DEFAULT_SECTOR_SIZE {
+                FileSystem::detect(reader, partition)
+                    .ok()
+                    .filter(|fs| *fs.fs_type() != FileSystemType::Unknown)
+                    .map(|fs| format!("{:?}", fs.fs_type()).to_lowercase())
+            } else {
+                None
+            };
+
+            let aligned = start.is_multiple_of(ALIGNMENT);
+            let physically_aligned = start.is_multiple_of(physical_boundary);
+            let extended = *table.table_type() == PartitionType::MBR
+                && MBR_EXTENDED.contains(&partition.type_id);
+            report.partitions.push(PartitionAlignment {
+                number: partition.number,
+                start,
+                size,
+                aligned,
+                physically_aligned,
+                extended,
+                filesystem,
+            });
+
+            // Only the partitions inside it hold data
+            if extended {
+                continue;
+            }
+            if !physically_aligned {
+                report.issues.push(AlignmentIssue {
+                    severity: Severity::Error,
+                    partition: Some(partition.number),
+                    message: format!(
+                        "starts at byte {} (sector {}), not on a {} byte physical sector: \
+                         I/O on 4K storage turns into read-modify-write",
+                        start, partition.start_lba, physical_boundary
+                    ),
+                    suggestion: Some("move it with guestctl align-check --realign".to_string()),
+                });
+            } else if !aligned {
+                report.issues.push(AlignmentIssue {
+                    severity: Severity::Warning,
+                    partition: Some(partition.number),
+                    message: format!(
+                        "starts at byte {}, not on a 1 MiB boundary: RAID stripes and SSD \
+                         erase blocks can be crossed",
+                        start
+                    ),
+                    suggestion: Some("move it with guestctl align-check --realign".to_string()),
+                });
+            }
+            if !size.is_multiple_of(PHYSICAL_SECTOR_SIZE) {
+                report.issues.push(AlignmentIssue {
+                    severity: Severity::Warning,
+                    partition: Some(partition.number),
+                    message: format!(
+                        "size {} is not a whole number of 4 KiB sectors and cannot be \
+                         represented on a 4Kn disk",
+                        size
+                    ),
+                    suggestion: Some(
+                        "shrink the partition to a multiple of 4 KiB before moving to 4Kn"
+                            .to_string(),
+                    ),
+                });
+            }
+        }
+
+        if table_sector_size != logical {
+            report.issues.push(AlignmentIssue {
+                severity: Severity::Error,
+                partition: None,
+                message: format!(
+                    "the partition table uses {}-byte sectors but the disk is presented \
+                     with {}-byte sectors: the guest will not find its partitions",
+                    table_sector_size, logical
+                ),
+                suggestion: Some(format!(
+                    "present the disk with {0}-byte sectors (QEMU: \
+                     logical_block_size={0},physical_block_size={0})",
+                    table_sector_size
+                )),
+            });
+        }
+        if report.block_device && logical < physical {
+            report.issues.push(AlignmentIssue {
+                severity: Severity::Warning,
+                partition: None,
+                message: format!(
+                    "{}-byte logical sectors on {}-byte physical sectors (512e): \
+                     writes smaller than a physical sector are emulated",
+                    logical, physical
+                ),
+                suggestion: None,
+            });
+        }
+        if *table.table_type() == PartitionType::MBR && table_sector_size == DEFAULT_SECTOR_SIZE {
+            report.issues.push(AlignmentIssue {
+                severity: Severity::Warning,
+                partition: None,
+                message: "MBR partition table: BIOS cannot boot from 4Kn disks".to_string(),
+                suggestion: Some(
+                    "convert to GPT and boot with UEFI before moving to 4Kn storage".to_string(),
+                ),
+            });
+        }
+
+        Ok(report)
+    }
+
+    /// Whether any partition starts off a 1 MiB boundary
+    pub fn needs_realignment(&self) -> bool {
+        self.partitions
+            .iter()
+            .any(|partition| !partition.aligned && !partition.extended)
+    }
+
+    /// Moves that put every partition on a 1 MiB boundary
+    ///
+    /// Partitions only move towards the end of the disk, keeping their
+    /// order and size; a partition pushed by the one before it moves too.
+    /// Fails when the disk has no room left at the end.
+    pub fn realignment_plan(&self) -> Result<Vec<PartitionMove>> {
+        if self.table_sector_size != DEFAULT_SECTOR_SIZE {
+            return Err(Error::Unsupported(format!(
+                "realigning partition tables with {}-byte sectors",
+                self.table_sector_size
+            )));
+        }
+
+        // Logical partitions live in a chain of boot records inside the
+        // extended partition, which moving would have to rewrite
+        if let Some(extended) = self.partitions.iter().find(|partition| partition.extended) {
+            return Err(Error::Unsupported(format!(
+                "realigning disks with an extended partition ({})",
+                extended.number
+            )));
+        }
+
+        let mut partitions: Vec<&PartitionAlignment> = self.partitions.iter().collect();
+        partitions.sort_by_key(|partition| partition.start);
+
+        let end_of_disk = match self.table_type.as_str() {
+            "gpt" => self.disk_size.saturating_sub(GPT_BACKUP_SIZE),
+            _ => self.disk_size,
+        };
+
+        let mut moves = Vec::new();
+        let mut next_free = 0;
+        for partition in partitions {
+            let to = partition.start.max(next_free).next_multiple_of(ALIGNMENT);
+            next_free = to + partition.size;
+            if to != partition.start {
+                moves.push(PartitionMove {
+                    number: partition.number,
+                    from: partition.start,
+                    to,
+                    size: partition.size,
+                });
+            }
+        }
+
+        if next_free > end_of_disk {
+            return Err(Error::InvalidOperation(format!(
+                "realigning needs {} more bytes at the end of the disk; grow the image first \
+                 (qemu-img resize IMAGE +{}M)",
+                next_free - end_of_disk,
+                (next_free - end_of_disk).div_ceil(ALIGNMENT)
+            )));
+        }
+
+        Ok(moves)
+    }
+}
+
+/// Point a moved FAT or NTFS boot sector at its new start
+///
+/// Both record the partition's start sector ("hidden sectors") and some
+/// boot code uses it to find the rest of the loader. Returns whether the
+/// partition held one.
+pub fn update_hidden_sectors(device: &Path, start: u64) -> Result<bool> {
+    let file = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(device)
+        .map_err(Error::Io)?;
+    let sector = start / DEFAULT_SECTOR_SIZE;
+    let hidden = u32::try_from(sector).map_err(|_| {
+        Error::InvalidOperation(format!("sector {} does not fit a boot sector", sector))
+    })?;
+
+    let mut boot = [0u8; 512];
+    file.read_exact_at(&mut boot, start).map_err(Error::Io)?;
+    let backup = if &boot[3..11] == b"NTFS    " {
+        // The backup boot sector is the sector after the volume
+        let total_sectors = u64::from_le_bytes(boot[0x28..0x30].try_into().unwrap());
+        Some(total_sectors)
+    } else if &boot[0x52..0x57] == b"FAT32" {
+        Some(u64::from(u16::from_le_bytes([boot[0x32], boot[0x33]])))
+    } else if &boot[0x36..0x39] == b"FAT" {
+        None
+    } else {
+        return Ok(false);
+    };
+
+    for relative in std::iter::once(0).chain(backup.filter(|&sector| sector != 0)) {
+        let offset = start + relative * DEFAULT_SECTOR_SIZE;
+        let mut sector = [0u8; 512];
+        if file.read_exact_at(&mut sector, offset).is_err() || sector[3..11] != boot[3..11] {
+            continue;
+        }
+        file.write_all_at(&hidden.to_le_bytes(), offset + 0x1c)
+            .map_err(Error::Io)?;
+    }
+    file.sync_all().map_err(Error::Io)?;
+    Ok(true)
+}
+
+/// Find the sector size a partition table was written for
+///
+/// GPT puts its header in the second sector, wherever that is. For MBR the
+/// table doesn't say; look for the first partition's filesystem at both
+/// scales.
+fn detect_table_sector_size(reader: &mut DiskReader) -> Result<u64> {
+    let mut header = [0u8; 8];
+    if reader
+        .read_exact_at(DEFAULT_SECTOR_SIZE, &mut header)
+        .is_ok()
+        && &header == b"EFI PART"
+    {
+        return Ok(DEFAULT_SECTOR_SIZE);
+    }
+    if reader
+        .read_exact_at(PHYSICAL_SECTOR_SIZE, &mut header)
+        .is_ok()
+        && &header == b"EFI PART"
+    {
+        return Ok(PHYSICAL_SECTOR_SIZE);
+    }
+
+    let table = PartitionTable::parse(reader)?;
+    if *table.table_type() != PartitionType::MBR {
+        return Ok(DEFAULT_SECTOR_SIZE);
+    }
+    let Some(first) = table
+        .partitions()
+        .iter()
+        .find(|partition| !MBR_EXTENDED.contains(&partition.type_id))
+    else {
+        return Ok(DEFAULT_SECTOR_SIZE);
+    };
+
+    let found_at = |reader: &mut DiskReader, partition: &Partition| {
+        FileSystem::detect(reader, partition)
+            .map(|fs| *fs.fs_type() != FileSystemType::Unknown)
+            .unwrap_or(false)
+    };
+    let scale = PHYSICAL_SECTOR_SIZE / DEFAULT_SECTOR_SIZE;
+    let scaled = Partition {
+        start_lba: first.start_lba * scale,
+        ..first.clone()
+    };
+    if !found_at(reader, first)
+        && scaled.start_lba * DEFAULT_SECTOR_SIZE < reader.size()
+        && found_at(reader, &scaled)
+    {
+        return Ok(PHYSICAL_SECTOR_SIZE);
+    }
+    Ok(DEFAULT_SECTOR_SIZE)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MIB: u64 = 1024 * 1024;
+
+    /// Raw image with MBR partitions given as (start sector, sectors, type)
+    fn mbr_image(path: &Path, size: u64, partitions: &[(u32, u32, u8)]) {
+        let mut mbr = vec![0u8; 512];
+        for (i, (start, sectors, type_id)) in partitions.iter().enumerate() {
+            let entry = 446 + i * 16;
+            mbr[entry + 4] = *type_id;
+            mbr[entry + 8..entry + 12].copy_from_slice(&start.to_le_bytes());
+            mbr[entry + 12..entry + 16].copy_from_slice(&sectors.to_le_bytes());
+        }
+        mbr[510] = 0x55;
+        mbr[511] = 0xaa;
+        let file = std::fs::File::create(path).unwrap();
+        file.set_len(size).unwrap();
+        file.write_all_at(&mbr, 0).unwrap();
+    }
+
+    fn analyze(path: &Path) -> AlignmentReport {
+        AlignmentReport::analyze(&mut DiskReader::open(path).unwrap()).unwrap()
+    }
+
+    #[test]
+    fn test_legacy_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("xp.img");
+        // Sector 63 start, and a second partition right after the first
+        mbr_image(&path, 64 * MIB, &[(63, 40897, 0x07), (40960, 40960, 0x83)]);
+
+        let report = analyze(&path);
+        assert_eq!(report.table_type, "msdos");
+        assert_eq!(report.table_sector_size, 512);
+        assert!(!report.partitions[0].physically_aligned);
+        assert!(report.partitions[1].aligned);
+        assert!(report.needs_realignment());
+        assert!(report
+            .issues
+            .iter()
+            .any(|issue| issue.partition == Some(1) && issue.severity == Severity::Error));
+
+        // Moving the first partition to 1 MiB pushes the second one along
+        let plan = report.realignment_plan().unwrap();
+        assert_eq!(
+            plan,
+            vec![
+                PartitionMove {
+                    number: 1,
+                    from: 63 * 512,
+                    to: MIB,
+                    size: 40897 * 512,
+                },
+                PartitionMove {
+                    number: 2,
+                    from: 20 * MIB,
+                    to: 21 * MIB,
+                    size: 20 * MIB,
+                },
+            ]
+        );
+
+        // No room left at the end of the disk
+        mbr_image(&path, 40 * MIB, &[(63, 40897, 0x07), (40960, 40960, 0x83)]);
+        let err = analyze(&path).realignment_plan().unwrap_err();
+        assert!(
+            err.to_string().contains("qemu-img resize IMAGE +1M"),
+            "{}",
+            err
+        );
+
+        mbr_image(&path, 64 * MIB, &[(63, 40897, 0x07), (40960, 40960, 0x05)]);
+        assert!(analyze(&path).realignment_plan().is_err());
+    }
+
+    #[test]
+    fn test_aligned_layout() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("modern.img");
+        mbr_image(
+            &path,
+            64 * MIB,
+            &[(2048, 40960, 0x83), (43008, 20480, 0x83)],
+        );
+
+        let report = analyze(&path);
+        assert!(!report.needs_realignment());
+        assert!(report.realignment_plan().unwrap().is_empty());
+        assert!(report
+            .issues
+            .iter()
+            .all(|issue| issue.partition.is_none() && issue.severity == Severity::Warning));
+    }
+
+    #[test]
+    fn test_4kn_gpt() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("4kn.img");
+        mbr_image(&path, 64 * MIB, &[(1, 16383, 0xee)]);
+
+        // GPT header in the second 4096-byte sector, entries in the third
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        let mut header = vec![0u8; 92];
+        header[0..8].copy_from_slice(b"EFI PART");
+        header[72..80].copy_from_slice(&2u64.to_le_bytes());
+        header[80..84].copy_from_slice(&128u32.to_le_bytes());
+        header[84..88].copy_from_slice(&128u32.to_le_bytes());
+        file.write_all_at(&header, 4096).unwrap();
+        let mut entry = vec![0u8; 128];
+        entry[0] = 0xaf;
+        entry[32..40].copy_from_slice(&256u64.to_le_bytes());
+        entry[40..48].copy_from_slice(&4095u64.to_le_bytes());
+        file.write_all_at(&entry, 2 * 4096).unwrap();
+
+        let report = analyze(&path);
+        assert_eq!(report.table_type, "gpt");
+        assert_eq!(report.table_sector_size, 4096);
+        assert_eq!(report.partitions[0].start, MIB);
+        assert_eq!(report.partitions[0].size, 15 * MIB);
+        let issue = report
+            .issues
+            .iter()
+            .find(|issue| issue.severity == Severity::Error)
+            .unwrap();
+        assert!(
+            issue.message.contains("4096-byte sectors"),
+            "{}",
+            issue.message
+        );
+        assert!(report.realignment_plan().is_err());
+    }
+
+    #[test]
+    fn test_update_hidden_sectors() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("ntfs.img");
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(true)
+            .open(&path)
+            .unwrap();
+        file.set_len(4 * MIB).unwrap();
+
+        // NTFS volume of 2047 sectors at 1 MiB, backup boot sector after it
+        let mut boot = [0u8; 512];
+        boot[3..11].copy_from_slice(b"NTFS    ");
+        boot[0x1c..0x20].copy_from_slice(&63u32.to_le_bytes());
+        boot[0x28..0x30].copy_from_slice(&2047u64.to_le_bytes());
+        file.write_all_at(&boot, MIB).unwrap();
+        file.write_all_at(&boot, MIB + 2047 * 512).unwrap();
+
+        assert!(update_hidden_sectors(&path, MIB).unwrap());
+        for offset in [MIB, MIB + 2047 * 512] {
+            let mut hidden = [0u8; 4];
+            file.read_exact_at(&mut hidden, offset + 0x1c).unwrap();
+            assert_eq!(u32::from_le_bytes(hidden), 2048);
+        }
+
+        // Not a FAT or NTFS boot sector
+        assert!(!update_hidden_sectors(&path, 2 * MIB).unwrap());
+    }
+}
@@ -0,0 +1,241 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Allocation maps
+//!
+//! Which guest ranges of an image hold data, read from its metadata (qcow2
+//! L2 tables, VMDK grain tables, the VHDX BAT) or, for raw files, asked of
+//! the filesystem with `SEEK_DATA` and `SEEK_HOLE`. Everything else reads
+//! as zeros, so copies can skip it without reading it.
+//!
+//! Maps are conservative: a range in the map may still read as zeros.
+
+use crate::core::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::fs::File;
+
+/// A range of guest bytes
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Extent {
+    pub offset: u64,
+    pub length: u64,
+}
+
+impl Extent {
+    pub fn end(&self) -> u64 {
+        self.offset + self.length
+    }
+}
+
+/// Sum of the extent lengths
+pub fn allocated_bytes(extents: &[Extent]) -> u64 {
+    extents.iter().map(|extent| extent.length).sum()
+}
+
+/// Append a range, merging it with the previous one when they touch
+pub(crate) fn push_extent(extents: &mut Vec<Extent>, offset: u64, length: u64) {
+    match extents.last_mut() {
+        Some(last) if last.offset + last.length == offset => last.length += length,
+        _ => extents.push(Extent { offset, length }),
+    }
+}
+
+/// Allocation of one layer of a chain, built in ascending guest order
+///
+/// Ranges the layer doesn't have are read from its parent, if any, and
+/// count as allocated where the parent has data.
+#[derive(Debug, Default)]
+pub(crate) struct LayerMap {
+    data: Vec<Extent>,
+    unallocated: Vec<Extent>,
+}
+
+impl LayerMap {
+    /// The layer holds data for the range
+    pub(crate) fn data(&mut self, offset: u64, length: u64) {
+        if length > 0 {
+            push_extent(&mut self.data, offset, length);
+        }
+    }
+
+    /// The layer doesn't have the range; reads go to the parent
+    pub(crate) fn unallocated(&mut self, offset: u64, length: u64) {
+        if length > 0 {
+            push_extent(&mut self.unallocated, offset, length);
+        }
+    }
+
+    /// The map of the chain, asking `parent` for its map only if needed
+    pub(crate) fn resolve<F>(self, parent: Option<F>) -> Result<Vec<Extent>>
+    where
+        F: FnOnce() -> Result<Vec<Extent>>,
+    {
+        let parent = match parent {
+            Some(parent) if !self.unallocated.is_empty() => parent()?,
+            _ => return Ok(self.data),
+        };
+
+        let mut inherited = Vec::new();
+        let (mut i, mut j) = (0, 0);
+        while i < self.unallocated.len() && j < parent.len() {
+            let (hole, extent) = (self.unallocated[i], parent[j]);
+            let start = hole.offset.max(extent.offset);
+            let end = hole.end().min(extent.end());
+            if start < end {
+                inherited.push(Extent {
+                    offset: start,
+                    length: end - start,
+                });
+            }
+            if hole.end() < extent.end() {
+                i += 1;
+            } else {
+                j += 1;
+            }
+        }
+
+        let mut merged = Vec::with_capacity(self.data.len() + inherited.len());
+        let (mut own, mut inherited) = (
+            self.data.into_iter().peekable(),
+            inherited.into_iter().peekable(),
+        );
+        loop {
+            let next = match (own.peek(), inherited.peek()) {
+                (Some(a), Some(b)) if a.offset <= b.offset => own.next(),
+                (Some(_), Some(_)) => inherited.next(),
+                (Some(_), None) => own.next(),
+                (None, Some(_)) => inherited.next(),
+                (None, None) => break,
+            };
+            let extent = next.unwrap();
+            push_extent(&mut merged, extent.offset, extent.length);
+        }
+        Ok(merged)
+    }
+}
+
+/// Data ranges of the first `size` bytes of a file, from the filesystem
+///
+/// Filesystems without `SEEK_DATA` (and block devices) report it all as
+/// data.
+pub(crate) fn file_extents(file: &File, size: u64) -> Result<Vec<Extent>> {
+    let whole = || {
+        if size == 0 {
+            Vec::new()
+        } else {
+            vec![Extent {
+                offset: 0,
+                length: size,
+            }]
+        }
+    };
+
+    #[cfg(target_os = "linux")]
+    {
+        use std::os::unix::fs::FileTypeExt;
+        use std::os::unix::io::AsRawFd;
+
+        if file
+            .metadata()
+            .map_err(Error::Io)?
+            .file_type()
+            .is_block_device()
+        {
+            return Ok(whole());
+        }
+
+        let fd = file.as_raw_fd();
+        let seek = |offset: u64, whence: libc::c_int| {
+            // SAFETY: lseek only moves the file position, which reads here
+            // never rely on (they are positioned)
+            let result = unsafe { libc::lseek(fd, offset as libc::off_t, whence) };
+            if result < 0 {
+                Err(std::io::Error::last_os_error())
+            } else {
+                Ok(result as u64)
+            }
+        };
+
+        let mut extents = Vec::new();
+        let mut offset = 0;
+        while offset < size {
+            let start = match seek(offset, libc::SEEK_DATA) {
+                Ok(start) => start,
+                // No data past `offset`
+                Err(e) if e.raw_os_error() == Some(libc::ENXIO) => break,
+                Err(e) if e.raw_os_error() == Some(libc::EINVAL) => return Ok(whole()),
+                Err(e) => return Err(Error::Io(e)),
+            };
+            if start >= size {
+                break;
+            }
+            let end = seek(start, libc::SEEK_HOLE).map_err(Error::Io)?.min(size);
+            push_extent(&mut extents, start, end - start);
+            offset = end;
+        }
+        Ok(extents)
+    }
+
+    #[cfg(not(target_os = "linux"))]
+    {
+        let _ = file;
+        Ok(whole())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn extent(offset: u64, length: u64) -> Extent {
+        Extent { offset, length }
+    }
+
+    #[test]
+    fn test_resolve_with_parent() {
+        let mut layer = LayerMap::default();
+        layer.data(0, 100);
+        layer.unallocated(100, 100);
+        layer.data(200, 50);
+        layer.unallocated(250, 750);
+
+        let parent = || Ok(vec![extent(50, 100), extent(400, 10), extent(900, 500)]);
+        assert_eq!(
+            layer.resolve(Some(parent)).unwrap(),
+            vec![
+                extent(0, 150),
+                extent(200, 50),
+                extent(400, 10),
+                extent(900, 100)
+            ]
+        );
+
+        let mut layer = LayerMap::default();
+        layer.data(10, 10);
+        layer.unallocated(20, 10);
+        assert_eq!(
+            layer.resolve(None::<fn() -> Result<Vec<Extent>>>).unwrap(),
+            vec![extent(10, 10)]
+        );
+    }
+
+    #[test]
+    fn test_file_extents() {
+        use std::os::unix::fs::FileExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sparse.raw");
+        let file = File::create(&path).unwrap();
+        let size = 64 * 1024 * 1024;
+        file.set_len(size).unwrap();
+        file.write_all_at(&[1u8; 4096], 32 * 1024 * 1024).unwrap();
+        file.sync_all().unwrap();
+
+        let extents = file_extents(&File::open(&path).unwrap(), size).unwrap();
+        // Filesystems without holes report everything
+        assert!(!extents.is_empty());
+        assert!(extents
+            .iter()
+            .any(|e| e.offset <= 32 * 1024 * 1024 && e.end() >= 32 * 1024 * 1024 + 4096));
+        assert!(allocated_bytes(&extents) <= size);
+        assert!(extents.iter().all(|e| e.end() <= size));
+    }
+}
@@ -0,0 +1,244 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! B-trees and object maps
+//!
+//! Nodes keep a table of contents of their entries at the start, keys after
+//! it and values from the end backwards; root nodes end with information on
+//! the whole tree. The object map of the container, and that of each volume,
+//! map the ids of virtual objects to where a transaction wrote them.
+
+use super::object::{
+    is_physical, le_u16, le_u32, le_u64, verify, OBJECT_HEADER_SIZE, TYPE_BTREE, TYPE_BTREE_NODE,
+    TYPE_OMAP,
+};
+use super::Apfs;
+use crate::core::{Error, Result};
+use std::cmp::Ordering;
+
+const BTNODE_ROOT: u16 = 0x1;
+const BTNODE_LEAF: u16 = 0x2;
+const BTNODE_FIXED_KV_SIZE: u16 = 0x4;
+const NODE_HEADER_SIZE: usize = OBJECT_HEADER_SIZE + 24;
+const BTREE_INFO_SIZE: usize = 40;
+/// Deeper trees than this are corrupt
+const MAX_LEVELS: u16 = 16;
+
+/// The only fixed size trees read are object maps
+const OMAP_KEY_SIZE: usize = 16;
+const OMAP_VAL_SIZE: usize = 16;
+/// Index nodes hold child ids, with a hash after them in sealed volumes
+const CHILD_SIZE: usize = 8;
+const OMAP_VAL_DELETED: u32 = 0x1;
+const OMAP_VAL_ENCRYPTED: u32 = 0x4;
+
+/// Value offset of entries that have no value
+const NO_VALUE: u16 = 0xffff;
+
+struct Node {
+    raw: Vec<u8>,
+    flags: u16,
+    level: u16,
+    count: usize,
+    toc: usize,
+    keys: usize,
+    values_end: usize,
+}
+
+impl Node {
+    fn parse(raw: Vec<u8>, oid: u64) -> Result<Self> {
+        let kind = if le_u16(&raw, 32) & BTNODE_ROOT != 0 {
+            TYPE_BTREE
+        } else {
+            TYPE_BTREE_NODE
+        };
+        verify(&raw, oid, kind)?;
+        let flags = le_u16(&raw, 32);
+        let toc = NODE_HEADER_SIZE + usize::from(le_u16(&raw, 40));
+        let keys = toc + usize::from(le_u16(&raw, 42));
+        let values_end = raw.len()
+            - if flags & BTNODE_ROOT != 0 {
+                BTREE_INFO_SIZE
+            } else {
+                0
+            };
+        let node = Self {
+            count: le_u32(&raw, 36) as usize,
+            level: le_u16(&raw, 34),
+            flags,
+            toc,
+            keys,
+            values_end,
+            raw,
+        };
+        let entry_size = if node.fixed() { 4 } else { 8 };
+        if keys > values_end || node.count > (keys - toc) / entry_size || node.level > MAX_LEVELS {
+            return Err(Error::InvalidFormat(format!(
+                "APFS object {}: corrupt B-tree node",
+                oid
+            )));
+        }
+        Ok(node)
+    }
+
+    fn fixed(&self) -> bool {
+        self.flags & BTNODE_FIXED_KV_SIZE != 0
+    }
+
+    fn is_leaf(&self) -> bool {
+        self.flags & BTNODE_LEAF != 0
+    }
+
+    /// Key and value of entry `i`
+    fn entry(&self, i: usize) -> Result<(&[u8], &[u8])> {
+        let (key_off, key_len, val_off, val_len) = if self.fixed() {
+            let at = self.toc + i * 4;
+            let val_len = if self.is_leaf() {
+                OMAP_VAL_SIZE
+            } else {
+                CHILD_SIZE
+            };
+            (
+                le_u16(&self.raw, at),
+                OMAP_KEY_SIZE,
+                le_u16(&self.raw, at + 2),
+                val_len,
+            )
+        } else {
+            let at = self.toc + i * 8;
+            (
+                le_u16(&self.raw, at),
+                usize::from(le_u16(&self.raw, at + 2)),
+                le_u16(&self.raw, at + 4),
+                usize::from(le_u16(&self.raw, at + 6)),
+            )
+        };
+        let corrupt = || Error::InvalidFormat("corrupt APFS B-tree entry".to_string());
+
+        let key_start = self.keys + usize::from(key_off);
+        let key = self
+            .raw
+            .get(key_start..key_start + key_len)
+            .filter(|_| key_start + key_len <= self.values_end)
+            .ok_or_else(corrupt)?;
+        let value = if val_off == NO_VALUE {
+            &[][..]
+        } else {
+            let val_start = self
+                .values_end
+                .checked_sub(usize::from(val_off))
+                .filter(|&start| start >= self.keys && start + val_len <= self.values_end)
+                .ok_or_else(corrupt)?;
+            &self.raw[val_start..val_start + val_len]
+        };
+        if !self.is_leaf() && value.len() < CHILD_SIZE {
+            return Err(corrupt());
+        }
+        Ok((key, value))
+    }
+}
+
+/// A tree to search: its root node, and for trees of virtual objects the
+/// object map their ids go through
+pub(super) struct Tree {
+    pub root: u64,
+    pub omap: Option<u64>,
+}
+
+impl Apfs {
+    /// Root node of the tree of the object map at `omap_oid`
+    pub(super) fn omap_tree(&self, omap_oid: u64) -> Result<u64> {
+        let raw = self.read_block(omap_oid)?;
+        verify(&raw, omap_oid, TYPE_OMAP)?;
+        if !is_physical(le_u32(&raw, 40)) {
+            return Err(Error::Unsupported(
+                "APFS object maps of virtual objects".to_string(),
+            ));
+        }
+        Ok(le_u64(&raw, 48))
+    }
+
+    /// Where the latest version of virtual object `oid` no newer than
+    /// transaction `xid` is, from the object map tree at `omap_root`
+    pub(super) fn omap_lookup(&self, omap_root: u64, oid: u64, xid: u64) -> Result<u64> {
+        let tree = Tree {
+            root: omap_root,
+            omap: None,
+        };
+        // Keys are the id and transaction, so the versions are contiguous
+        let entries = self.scan(&tree, &|key| {
+            le_u64(key, 0).cmp(&oid).then(if le_u64(key, 8) > xid {
+                Ordering::Greater
+            } else {
+                Ordering::Equal
+            })
+        })?;
+        let (_, value) = entries
+            .last()
+            .ok_or_else(|| Error::NotFound(format!("APFS object {}", oid)))?;
+        let flags = le_u32(value, 0);
+        if flags & OMAP_VAL_DELETED != 0 {
+            return Err(Error::NotFound(format!("APFS object {}", oid)));
+        }
+        if flags & OMAP_VAL_ENCRYPTED != 0 {
+            return Err(Error::Unsupported("encrypted APFS metadata".to_string()));
+        }
+        Ok(le_u64(value, 8))
+    }
+
+    /// Leaf entries of `tree` whose keys `range` finds `Equal`, in key
+    /// order; `range` orders keys against a contiguous range of them
+    pub(super) fn scan(
+        &self,
+        tree: &Tree,
+        range: &dyn Fn(&[u8]) -> Ordering,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut out = Vec::new();
+        let root = self.read_node(tree, tree.root)?;
+        self.scan_node(tree, &root, range, &mut out)?;
+        Ok(out)
+    }
+
+    fn read_node(&self, tree: &Tree, oid: u64) -> Result<Node> {
+        let addr = match tree.omap {
+            Some(omap) => self.omap_lookup(omap, oid, self.container.xid)?,
+            None => oid,
+        };
+        Node::parse(self.read_block(addr)?, oid)
+    }
+
+    fn scan_node(
+        &self,
+        tree: &Tree,
+        node: &Node,
+        range: &dyn Fn(&[u8]) -> Ordering,
+        out: &mut Vec<(Vec<u8>, Vec<u8>)>,
+    ) -> Result<()> {
+        if node.is_leaf() {
+            for i in 0..node.count {
+                let (key, value) = node.entry(i)?;
+                if range(key) == Ordering::Equal {
+                    out.push((key.to_vec(), value.to_vec()));
+                }
+            }
+            return Ok(());
+        }
+
+        // Child i holds the keys from key i up to key i + 1
+        for i in 0..node.count {
+            let (key, value) = node.entry(i)?;
+            if range(key) == Ordering::Greater {
+                break;
+            }
+            if i + 1 < node.count && range(node.entry(i + 1)?.0) == Ordering::Less {
+                continue;
+            }
+            let child = self.read_node(tree, le_u64(value, 0))?;
+            if child.level + 1 != node.level {
+                return Err(Error::InvalidFormat(
+                    "corrupt APFS B-tree: bad node level".to_string(),
+                ));
+            }
+            self.scan_node(tree, &child, range, out)?;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,459 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Volumes and their file system records
+//!
+//! A volume keeps inodes, directory entries, extended attributes and file
+//! extents in one tree, keyed by object id and record type. Sealed system
+//! volumes keep file extents in a tree of their own.
+
+use super::btree::Tree;
+use super::object::{is_physical, le_u16, le_u32, le_u64, verify, TYPE_FS};
+use super::{Apfs, ApfsVolume};
+use crate::core::{Error, Result};
+use crate::disk::decmpfs;
+use std::cmp::Ordering;
+use uuid::Uuid;
+
+const FS_MAGIC: &[u8; 4] = b"APSB";
+const FS_UNENCRYPTED: u64 = 0x1;
+
+const INCOMPAT_CASE_INSENSITIVE: u64 = 0x1;
+const INCOMPAT_DATALESS_SNAPS: u64 = 0x2;
+const INCOMPAT_ENC_ROLLED: u64 = 0x4;
+const INCOMPAT_NORMALIZATION_INSENSITIVE: u64 = 0x8;
+const INCOMPAT_SEALED_VOLUME: u64 = 0x20;
+const INCOMPAT_READ: u64 = INCOMPAT_CASE_INSENSITIVE
+    | INCOMPAT_DATALESS_SNAPS
+    | INCOMPAT_ENC_ROLLED
+    | INCOMPAT_NORMALIZATION_INSENSITIVE
+    | INCOMPAT_SEALED_VOLUME;
+
+/// Record types, in the top bits of the object id of keys
+const OBJ_ID_MASK: u64 = (1 << 60) - 1;
+const OBJ_TYPE_SHIFT: u32 = 60;
+const APFS_TYPE_INODE: u64 = 3;
+const APFS_TYPE_XATTR: u64 = 4;
+const APFS_TYPE_FILE_EXTENT: u64 = 8;
+const APFS_TYPE_DIR_REC: u64 = 9;
+
+pub(super) const ROOT_DIR_INO: u64 = 2;
+
+/// Inode values: fixed fields, then extended fields
+const INODE_XFIELDS_OFFSET: usize = 92;
+const INO_EXT_TYPE_DSTREAM: u8 = 8;
+
+const DREC_TYPE_MASK: u16 = 0xf;
+const DREC_NAME_LEN_MASK: u32 = 0x3ff;
+
+const XATTR_DATA_STREAM: u16 = 0x1;
+const SYMLINK_XATTR: &str = "com.apple.fs.symlink";
+
+const EXTENT_LEN_MASK: u64 = (1 << 56) - 1;
+
+const S_IFMT: u16 = 0o170000;
+const S_IFLNK: u16 = 0o120000;
+const S_IFREG: u16 = 0o100000;
+const S_IFDIR: u16 = 0o040000;
+
+const MAX_SYMLINKS: usize = 40;
+const NSEC_PER_SEC: u64 = 1_000_000_000;
+
+/// Volume roles; the ones past the first few are counted in steps of 64
+fn role_name(role: u16) -> Option<&'static str> {
+    Some(match role {
+        0x1 => "System",
+        0x2 => "User",
+        0x4 => "Recovery",
+        0x8 => "VM",
+        0x10 => "Preboot",
+        0x20 => "Installer",
+        0x40 => "Data",
+        0x80 => "Baseband",
+        0xc0 => "Update",
+        0x100 => "xART",
+        0x140 => "Hardware",
+        0x180 => "Backup",
+        0x240 => "Enterprise",
+        0x2c0 => "Prelogin",
+        _ => return None,
+    })
+}
+
+fn record_key(oid: u64, kind: u64) -> impl Fn(&[u8]) -> Ordering {
+    move |key: &[u8]| {
+        let hdr = le_u64(key, 0);
+        (hdr & OBJ_ID_MASK, hdr >> OBJ_TYPE_SHIFT).cmp(&(oid, kind))
+    }
+}
+
+/// Name of a directory entry or extended attribute, without its NUL
+fn name_bytes(raw: &[u8]) -> &[u8] {
+    raw.strip_suffix(&[0]).unwrap_or(raw)
+}
+
+pub(super) struct Inode {
+    pub id: u64,
+    pub parent_id: u64,
+    /// Id of the data stream, usually the inode's own
+    pub private_id: u64,
+    pub mtime: u64,
+    pub bsd_flags: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub mode: u16,
+    pub links: u32,
+    pub size: u64,
+}
+
+impl Inode {
+    fn parse(id: u64, value: &[u8]) -> Result<Self> {
+        let corrupt = || Error::InvalidFormat(format!("APFS inode {}: corrupt record", id));
+        if value.len() < INODE_XFIELDS_OFFSET {
+            return Err(corrupt());
+        }
+        let mut inode = Self {
+            id,
+            parent_id: le_u64(value, 0),
+            private_id: le_u64(value, 8),
+            mtime: le_u64(value, 24),
+            bsd_flags: le_u32(value, 68),
+            uid: le_u32(value, 72),
+            gid: le_u32(value, 76),
+            mode: le_u16(value, 80),
+            links: le_u32(value, 56),
+            size: 0,
+        };
+
+        // Extended fields: a count, their types and sizes, then their data
+        // each padded to 8 bytes
+        if value.len() >= INODE_XFIELDS_OFFSET + 4 {
+            let count = usize::from(le_u16(value, INODE_XFIELDS_OFFSET));
+            let mut data = INODE_XFIELDS_OFFSET + 4 + count * 4;
+            for i in 0..count {
+                let field = INODE_XFIELDS_OFFSET + 4 + i * 4;
+                let (kind, size) = match value.get(field..field + 4) {
+                    Some(field) => (field[0], usize::from(le_u16(field, 2))),
+                    None => return Err(corrupt()),
+                };
+                if kind == INO_EXT_TYPE_DSTREAM {
+                    let dstream = value.get(data..data + 8).ok_or_else(corrupt)?;
+                    inode.size = le_u64(dstream, 0);
+                }
+                data += (size + 7) & !7;
+            }
+        }
+        Ok(inode)
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.mode & S_IFMT == S_IFDIR
+    }
+
+    pub fn is_reg(&self) -> bool {
+        self.mode & S_IFMT == S_IFREG
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.mode & S_IFMT == S_IFLNK
+    }
+
+    /// Modification time in seconds since the Unix epoch
+    pub fn mtime_secs(&self) -> i64 {
+        (self.mtime / NSEC_PER_SEC) as i64
+    }
+}
+
+pub(super) struct DirEntry {
+    pub name: Vec<u8>,
+    pub ino: u64,
+    pub file_type: u8,
+}
+
+/// A mounted volume: its superblock read, and the trees its files are in
+pub struct ApfsFs<'a> {
+    apfs: &'a Apfs,
+    info: ApfsVolume,
+    fs_tree: Tree,
+    /// File extents of sealed volumes
+    fext_tree: Option<Tree>,
+    /// Directory entry keys have a hash of the name before it
+    hashed_names: bool,
+    case_insensitive: bool,
+}
+
+impl Apfs {
+    /// Superblock of volume `index`
+    pub(super) fn volume_superblock(&self, index: usize) -> Result<Vec<u8>> {
+        let oid = *self
+            .container
+            .fs_oids
+            .get(index)
+            .ok_or_else(|| Error::NotFound(format!("APFS volume {}", index)))?;
+        let addr = self.omap_lookup(self.omap_root, oid, self.container.xid)?;
+        let raw = self.read_block(addr)?;
+        verify(&raw, oid, TYPE_FS)?;
+        if &raw[32..36] != FS_MAGIC {
+            return Err(Error::InvalidFormat(format!(
+                "APFS volume {}: bad magic",
+                index
+            )));
+        }
+        Ok(raw)
+    }
+
+    pub(super) fn volume_info(&self, index: usize, raw: &[u8]) -> ApfsVolume {
+        let name = &raw[704..960];
+        let end = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+        ApfsVolume {
+            index,
+            name: String::from_utf8_lossy(&name[..end]).into_owned(),
+            role: role_name(le_u16(raw, 964)).map(str::to_string),
+            uuid: Uuid::from_bytes(raw[240..256].try_into().unwrap()),
+            encrypted: le_u64(raw, 264) & FS_UNENCRYPTED == 0,
+        }
+    }
+
+    pub(super) fn open_volume(&self, index: usize) -> Result<ApfsFs<'_>> {
+        let raw = self.volume_superblock(index)?;
+        let info = self.volume_info(index, &raw);
+        if info.encrypted {
+            return Err(Error::Unsupported(format!(
+                "APFS volume {} is encrypted",
+                info.name
+            )));
+        }
+        let incompat = le_u64(&raw, 56);
+        if incompat & !INCOMPAT_READ != 0 {
+            return Err(Error::Unsupported(format!(
+                "APFS volume incompatible features 0x{:x}",
+                incompat & !INCOMPAT_READ
+            )));
+        }
+
+        let omap = self.omap_tree(le_u64(&raw, 128))?;
+        let tree = |oid: u64, tree_type: u32| Tree {
+            root: oid,
+            omap: if is_physical(tree_type) {
+                None
+            } else {
+                Some(omap)
+            },
+        };
+        let fext_tree = if incompat & INCOMPAT_SEALED_VOLUME != 0 {
+            Some(tree(le_u64(&raw, 1032), le_u32(&raw, 1040)))
+        } else {
+            None
+        };
+        Ok(ApfsFs {
+            apfs: self,
+            info,
+            fs_tree: tree(le_u64(&raw, 136), le_u32(&raw, 116)),
+            fext_tree,
+            hashed_names: incompat
+                & (INCOMPAT_CASE_INSENSITIVE | INCOMPAT_NORMALIZATION_INSENSITIVE)
+                != 0,
+            case_insensitive: incompat & INCOMPAT_CASE_INSENSITIVE != 0,
+        })
+    }
+}
+
+impl ApfsFs<'_> {
+    /// The volume's name, role and UUID
+    pub fn info(&self) -> &ApfsVolume {
+        &self.info
+    }
+
+    fn records(&self, oid: u64, kind: u64) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        self.apfs.scan(&self.fs_tree, &record_key(oid, kind))
+    }
+
+    pub(super) fn inode(&self, id: u64) -> Result<Inode> {
+        let records = self.records(id, APFS_TYPE_INODE)?;
+        let (_, value) = records
+            .first()
+            .ok_or_else(|| Error::NotFound(format!("APFS inode {}", id)))?;
+        Inode::parse(id, value)
+    }
+
+    /// Entries of directory `dir`; "." and ".." aren't stored
+    pub(super) fn entries(&self, dir: &Inode) -> Result<Vec<DirEntry>> {
+        let corrupt = || Error::InvalidFormat(format!("APFS directory {} is corrupt", dir.id));
+        self.records(dir.id, APFS_TYPE_DIR_REC)?
+            .into_iter()
+            .map(|(key, value)| {
+                let name = if self.hashed_names {
+                    let len = (le_u32(&key, 8) & DREC_NAME_LEN_MASK) as usize;
+                    key.get(12..12 + len)
+                } else {
+                    let len = usize::from(le_u16(&key, 8));
+                    key.get(10..10 + len)
+                };
+                let name = name.ok_or_else(corrupt)?;
+                if value.len() < 18 {
+                    return Err(corrupt());
+                }
+                Ok(DirEntry {
+                    name: name_bytes(name).to_vec(),
+                    ino: le_u64(&value, 0),
+                    file_type: (le_u16(&value, 16) & DREC_TYPE_MASK) as u8,
+                })
+            })
+            .collect()
+    }
+
+    fn lookup(&self, dir: &Inode, name: &[u8]) -> Result<Option<DirEntry>> {
+        let entries = self.entries(dir)?;
+        if let Some(i) = entries.iter().position(|entry| entry.name == name) {
+            return Ok(entries.into_iter().nth(i));
+        }
+        if !self.case_insensitive {
+            return Ok(None);
+        }
+        let folded = String::from_utf8_lossy(name).to_lowercase();
+        Ok(entries
+            .into_iter()
+            .find(|entry| String::from_utf8_lossy(&entry.name).to_lowercase() == folded))
+    }
+
+    /// Inode at `path`, following a final symbolic link if `follow`
+    pub(super) fn resolve(&self, path: &str, follow: bool) -> Result<Inode> {
+        let mut pending: Vec<Vec<u8>> = components(path).rev().collect();
+        let mut current = self.inode(ROOT_DIR_INO)?;
+        let mut symlinks = 0;
+        while let Some(name) = pending.pop() {
+            if !current.is_dir() {
+                return Err(Error::NotFound(format!("{}: not a directory", path)));
+            }
+            // Directories have no ".." entries, only their parent's id
+            if name == b".." {
+                if current.id != ROOT_DIR_INO {
+                    current = self.inode(current.parent_id)?;
+                }
+                continue;
+            }
+            let entry = self
+                .lookup(&current, &name)?
+                .ok_or_else(|| Error::NotFound(path.to_string()))?;
+            let inode = self.inode(entry.ino)?;
+            if inode.is_symlink() && (follow || !pending.is_empty()) {
+                symlinks += 1;
+                if symlinks > MAX_SYMLINKS {
+                    return Err(Error::InvalidFormat(format!(
+                        "{}: too many levels of symbolic links",
+                        path
+                    )));
+                }
+                let target = self.link_target(&inode)?;
+                if target.starts_with(b"/") {
+                    current = self.inode(ROOT_DIR_INO)?;
+                }
+                let target = String::from_utf8_lossy(&target).into_owned();
+                pending.extend(components(&target).rev());
+                continue;
+            }
+            current = inode;
+        }
+        Ok(current)
+    }
+
+    /// Value of extended attribute `name` of `inode`, if it has it
+    pub(super) fn xattr(&self, inode: &Inode, name: &str) -> Result<Option<Vec<u8>>> {
+        let corrupt =
+            || Error::InvalidFormat(format!("APFS inode {}: corrupt attribute", inode.id));
+        for (key, value) in self.records(inode.id, APFS_TYPE_XATTR)? {
+            let len = usize::from(le_u16(&key, 8));
+            let key_name = key.get(10..10 + len).ok_or_else(corrupt)?;
+            if name_bytes(key_name) != name.as_bytes() {
+                continue;
+            }
+            if value.len() < 4 {
+                return Err(corrupt());
+            }
+            let data = value
+                .get(4..4 + usize::from(le_u16(&value, 2)))
+                .ok_or_else(corrupt)?;
+            if le_u16(&value, 0) & XATTR_DATA_STREAM == 0 {
+                return Ok(Some(data.to_vec()));
+            }
+            // Large values are in a data stream of their own
+            if data.len() < 16 {
+                return Err(corrupt());
+            }
+            return self.read_stream(le_u64(data, 0), le_u64(data, 8)).map(Some);
+        }
+        Ok(None)
+    }
+
+    /// Size of the content of a regular file, compressed or not
+    pub(super) fn data_size(&self, inode: &Inode) -> Result<u64> {
+        if inode.bsd_flags & decmpfs::UF_COMPRESSED != 0 {
+            if let Some(header) = self.xattr(inode, decmpfs::XATTR)? {
+                return Ok(decmpfs::uncompressed_size(&header).unwrap_or(0));
+            }
+        }
+        Ok(inode.size)
+    }
+
+    pub(super) fn link_target(&self, inode: &Inode) -> Result<Vec<u8>> {
+        let target = self.xattr(inode, SYMLINK_XATTR)?.ok_or_else(|| {
+            Error::InvalidFormat(format!(
+                "APFS inode {}: symbolic link without target",
+                inode.id
+            ))
+        })?;
+        Ok(name_bytes(&target).to_vec())
+    }
+
+    /// Content of a regular file, decompressed if stored compressed
+    pub(super) fn read_data(&self, inode: &Inode) -> Result<Vec<u8>> {
+        if inode.bsd_flags & decmpfs::UF_COMPRESSED != 0 {
+            if let Some(header) = self.xattr(inode, decmpfs::XATTR)? {
+                return decmpfs::decompress(&header, || {
+                    self.xattr(inode, decmpfs::RESOURCE_FORK_XATTR)?
+                        .ok_or_else(|| {
+                            Error::InvalidFormat(format!(
+                                "APFS inode {}: compressed data without resource fork",
+                                inode.id
+                            ))
+                        })
+                });
+            }
+        }
+        self.read_stream(inode.private_id, inode.size)
+    }
+
+    /// The first `size` bytes of data stream `id`; holes read as zeros
+    fn read_stream(&self, id: u64, size: u64) -> Result<Vec<u8>> {
+        let len = usize::try_from(size)
+            .map_err(|_| Error::ResourceLimit(format!("APFS stream {}: {} bytes", id, size)))?;
+        let extents = match &self.fext_tree {
+            Some(tree) => self.apfs.scan(tree, &|key| le_u64(key, 0).cmp(&id))?,
+            None => self.records(id, APFS_TYPE_FILE_EXTENT)?,
+        };
+
+        let mut data = vec![0u8; len];
+        for (key, value) in extents {
+            if value.len() < 16 {
+                return Err(Error::InvalidFormat(format!(
+                    "APFS stream {}: corrupt extent",
+                    id
+                )));
+            }
+            let logical = le_u64(&key, 8);
+            let extent_len = le_u64(&value, 0) & EXTENT_LEN_MASK;
+            let block = le_u64(&value, 8);
+            if block == 0 || logical >= size {
+                continue;
+            }
+            let end = logical.saturating_add(extent_len).min(size);
+            self.apfs
+                .read_blocks(block, &mut data[logical as usize..end as usize])?;
+        }
+        Ok(data)
+    }
+}
+
+fn components(path: &str) -> impl DoubleEndedIterator<Item = Vec<u8>> + '_ {
+    path.split('/')
+        .filter(|name| !name.is_empty() && *name != ".")
+        .map(|name| name.as_bytes().to_vec())
+}
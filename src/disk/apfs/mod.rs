@@ -0,0 +1,485 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Pure Rust APFS reader
+//!
+//! Reads APFS containers in disk images without mounting them, so macOS
+//! guests can be listed, inspected for their version and have files
+//! extracted where no APFS driver is available.
+//!
+//! The container is read as of its latest checkpoint, and each of its
+//! volumes (System, Data, Preboot, Recovery, VM) can be opened. Signed
+//! system volumes, case-insensitive names and files compressed with zlib
+//! or LZVN are read. Encrypted volumes, snapshots and Fusion containers
+//! are refused.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use guestkit::disk::Apfs;
+//!
+//! // The container of a macOS image, in a partition at 200 MiB
+//! let apfs = Apfs::open("/srv/images/macos.img", 200 * 1024 * 1024)?;
+//! for volume in apfs.volumes()? {
+//!     println!("{} {:?}", volume.name, volume.role);
+//! }
+//! let system = apfs.volume(0)?;
+//! let plist = system.read_file("/System/Library/CoreServices/SystemVersion.plist")?;
+//! # Ok::<(), guestkit::Error>(())
+//! ```
+
+mod btree;
+mod fs;
+mod object;
+
+pub use fs::ApfsFs;
+
+use crate::core::{Error, Result};
+use crate::disk::DiskReader;
+use object::Container;
+use serde::Serialize;
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+use uuid::Uuid;
+
+/// A volume of the container
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ApfsVolume {
+    pub index: usize,
+    pub name: String,
+    /// System, Data, Preboot, Recovery, VM, ... if the volume has a role
+    pub role: Option<String>,
+    pub uuid: Uuid,
+    pub encrypted: bool,
+}
+
+/// File metadata, as `lstat` reports it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ApfsStat {
+    pub ino: u64,
+    /// File type and permission bits
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    /// Hard links of files; APFS counts the children of directories instead
+    pub links: u32,
+    pub mtime: i64,
+}
+
+/// A directory entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ApfsDirEntry {
+    pub name: String,
+    pub ino: u64,
+    /// Type from the entry, as in `d_type`: 4 directory, 8 regular file,
+    /// 10 symbolic link
+    pub file_type: u8,
+}
+
+/// Where the container is: a raw image or device, or any image through
+/// the disk reader
+enum Source {
+    File(File),
+    Reader(Box<DiskReader>),
+}
+
+impl Source {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        match self {
+            Source::File(file) => file.read_exact_at(buf, offset).map_err(Error::Io),
+            Source::Reader(reader) => reader.pread_exact(offset, buf),
+        }
+    }
+}
+
+/// An APFS container at `offset` of a disk image
+pub struct Apfs {
+    source: Source,
+    offset: u64,
+    container: Container,
+    /// Root of the tree of the container's object map
+    omap_root: u64,
+}
+
+impl Apfs {
+    /// Open the container at `offset` of a raw image or device
+    pub fn open<P: AsRef<Path>>(path: P, offset: u64) -> Result<Self> {
+        let file = File::open(path.as_ref())?;
+        Self::load(Source::File(file), offset)
+    }
+
+    /// Read the container at `offset` of any image the disk reader opens
+    pub fn from_reader(reader: DiskReader, offset: u64) -> Result<Self> {
+        Self::load(Source::Reader(Box::new(reader)), offset)
+    }
+
+    fn load(source: Source, offset: u64) -> Result<Self> {
+        let mut raw = vec![0u8; 4096];
+        source.read_at(offset, &mut raw)?;
+        let block_size = Container::block_size(&raw)?;
+        raw.resize(block_size as usize, 0);
+        source.read_at(offset, &mut raw)?;
+        let mut container = Container::parse(&raw)?;
+
+        // Block 0 may be older than the last checkpoint written
+        let mut copy = vec![0u8; block_size as usize];
+        let blocks: Vec<u64> = container.checkpoint_blocks().collect();
+        for block in blocks {
+            if block >= container.block_count {
+                break;
+            }
+            source.read_at(offset + block * block_size, &mut copy)?;
+            if Container::is_newer(&copy, container.xid) {
+                container = Container::parse(&copy)?;
+            }
+        }
+
+        let mut apfs = Self {
+            source,
+            offset,
+            container,
+            omap_root: 0,
+        };
+        apfs.omap_root = apfs.omap_tree(apfs.container.omap_oid)?;
+        Ok(apfs)
+    }
+
+    pub fn uuid(&self) -> Uuid {
+        Uuid::from_bytes(self.container.uuid)
+    }
+
+    pub fn block_size(&self) -> u64 {
+        self.container.block_size
+    }
+
+    /// Volumes of the container, in the order `volume` opens them
+    pub fn volumes(&self) -> Result<Vec<ApfsVolume>> {
+        (0..self.container.fs_oids.len())
+            .map(|index| {
+                let raw = self.volume_superblock(index)?;
+                Ok(self.volume_info(index, &raw))
+            })
+            .collect()
+    }
+
+    /// Open volume `index` to read its files
+    pub fn volume(&self, index: usize) -> Result<ApfsFs<'_>> {
+        self.open_volume(index)
+    }
+
+    fn read_block(&self, addr: u64) -> Result<Vec<u8>> {
+        let mut raw = vec![0u8; self.container.block_size as usize];
+        self.read_blocks(addr, &mut raw)?;
+        Ok(raw)
+    }
+
+    /// Read `buf.len()` bytes from block `addr` on
+    fn read_blocks(&self, addr: u64, buf: &mut [u8]) -> Result<()> {
+        let blocks = (buf.len() as u64).div_ceil(self.container.block_size);
+        if addr.saturating_add(blocks) > self.container.block_count {
+            return Err(Error::InvalidFormat(format!(
+                "APFS block {} past the end of the container",
+                addr
+            )));
+        }
+        self.source
+            .read_at(self.offset + addr * self.container.block_size, buf)
+    }
+}
+
+impl ApfsFs<'_> {
+    /// Metadata of `path`, not following a final symbolic link
+    pub fn stat(&self, path: &str) -> Result<ApfsStat> {
+        let inode = self.resolve(path, false)?;
+        Ok(ApfsStat {
+            ino: inode.id,
+            mode: u32::from(inode.mode),
+            uid: inode.uid,
+            gid: inode.gid,
+            size: self.data_size(&inode)?,
+            links: inode.links,
+            mtime: inode.mtime_secs(),
+        })
+    }
+
+    pub fn exists(&self, path: &str) -> Result<bool> {
+        match self.resolve(path, false) {
+            Ok(_) => Ok(true),
+            Err(Error::NotFound(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn read_file(&self, path: &str) -> Result<Vec<u8>> {
+        let inode = self.resolve(path, true)?;
+        if !inode.is_reg() {
+            return Err(Error::InvalidOperation(format!(
+                "{}: not a regular file",
+                path
+            )));
+        }
+        self.read_data(&inode)
+    }
+
+    pub fn read_link(&self, path: &str) -> Result<String> {
+        let inode = self.resolve(path, false)?;
+        if !inode.is_symlink() {
+            return Err(Error::InvalidOperation(format!(
+                "{}: not a symbolic link",
+                path
+            )));
+        }
+        let target = self.link_target(&inode)?;
+        Ok(String::from_utf8_lossy(&target).into_owned())
+    }
+
+    /// Entries of directory `path`; APFS stores no "." or ".."
+    pub fn list_dir(&self, path: &str) -> Result<Vec<ApfsDirEntry>> {
+        let dir = self.resolve(path, true)?;
+        if !dir.is_dir() {
+            return Err(Error::NotFound(format!("{}: not a directory", path)));
+        }
+        Ok(self
+            .entries(&dir)?
+            .into_iter()
+            .map(|entry| ApfsDirEntry {
+                name: String::from_utf8_lossy(&entry.name).into_owned(),
+                ino: entry.ino,
+                file_type: entry.file_type,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::object::fletcher64;
+    use super::*;
+    use std::io::Write;
+
+    const BLOCK: usize = 4096;
+    const XID: u64 = 5;
+    const VOLUME_OID: u64 = 1026;
+    const FS_TREE_OID: u64 = 1028;
+    const SYSTEM: u64 = 16;
+    const HELLO: u64 = 17;
+    const LINK: u64 = 18;
+    const PLIST: u64 = 19;
+
+    /// Write an object header and checksum into block `at` of `image`
+    fn object(image: &mut [u8], at: usize, oid: u64, kind: u32, body: &[u8]) {
+        let raw = &mut image[at * BLOCK..(at + 1) * BLOCK];
+        raw[32..32 + body.len()].copy_from_slice(body);
+        raw[8..16].copy_from_slice(&oid.to_le_bytes());
+        raw[16..24].copy_from_slice(&XID.to_le_bytes());
+        raw[24..28].copy_from_slice(&kind.to_le_bytes());
+        let sum = fletcher64(raw);
+        raw[0..8].copy_from_slice(&sum.to_le_bytes());
+    }
+
+    /// Body of a root leaf node; fixed size entries are object map ones
+    fn leaf(entries: &[(Vec<u8>, Vec<u8>)], fixed: bool) -> Vec<u8> {
+        let entry_size = if fixed { 4 } else { 8 };
+        let toc_len = entries.len() * entry_size;
+        let mut body = vec![0u8; BLOCK - 32];
+        body[0..2].copy_from_slice(&(0x1u16 | 0x2 | if fixed { 0x4 } else { 0 }).to_le_bytes());
+        body[4..8].copy_from_slice(&(entries.len() as u32).to_le_bytes());
+        body[10..12].copy_from_slice(&(toc_len as u16).to_le_bytes());
+        let (mut key_off, mut val_off) = (0, 0);
+        let keys = 24 + toc_len;
+        let values_end = BLOCK - 32 - 40;
+        for (i, (key, value)) in entries.iter().enumerate() {
+            val_off += value.len();
+            let toc = 24 + i * entry_size;
+            let mut loc = (key_off as u16).to_le_bytes().to_vec();
+            if !fixed {
+                loc.extend_from_slice(&(key.len() as u16).to_le_bytes());
+            }
+            loc.extend_from_slice(&(val_off as u16).to_le_bytes());
+            if !fixed {
+                loc.extend_from_slice(&(value.len() as u16).to_le_bytes());
+            }
+            body[toc..toc + entry_size].copy_from_slice(&loc);
+            body[keys + key_off..keys + key_off + key.len()].copy_from_slice(key);
+            body[values_end - val_off..values_end - val_off + value.len()].copy_from_slice(value);
+            key_off += (key.len() + 7) & !7;
+        }
+        body
+    }
+
+    fn omap_entry(oid: u64, addr: u64) -> (Vec<u8>, Vec<u8>) {
+        let key = [oid.to_le_bytes(), XID.to_le_bytes()].concat();
+        let mut value = vec![0u8; 8];
+        value[4..8].copy_from_slice(&(BLOCK as u32).to_le_bytes());
+        value.extend_from_slice(&addr.to_le_bytes());
+        (key, value)
+    }
+
+    fn omap(tree: u64) -> Vec<u8> {
+        let mut body = vec![0u8; 24];
+        body[8..12].copy_from_slice(&0x4000_0002u32.to_le_bytes());
+        body[16..24].copy_from_slice(&tree.to_le_bytes());
+        body
+    }
+
+    fn key(oid: u64, kind: u64) -> Vec<u8> {
+        (oid | (kind << 60)).to_le_bytes().to_vec()
+    }
+
+    fn inode(
+        oid: u64,
+        parent: u64,
+        mode: u16,
+        flags: u32,
+        size: Option<u64>,
+    ) -> (Vec<u8>, Vec<u8>) {
+        let mut value = vec![0u8; 92];
+        value[0..8].copy_from_slice(&parent.to_le_bytes());
+        value[8..16].copy_from_slice(&oid.to_le_bytes());
+        value[24..32].copy_from_slice(&1_700_000_000_000_000_000u64.to_le_bytes());
+        value[56..60].copy_from_slice(&1u32.to_le_bytes());
+        value[68..72].copy_from_slice(&flags.to_le_bytes());
+        value[72..76].copy_from_slice(&501u32.to_le_bytes());
+        value[76..80].copy_from_slice(&20u32.to_le_bytes());
+        value[80..82].copy_from_slice(&mode.to_le_bytes());
+        if let Some(size) = size {
+            // A data stream extended field
+            value.extend_from_slice(&[1, 0, 40, 0, 8, 0, 40, 0]);
+            value.extend_from_slice(&size.to_le_bytes());
+            value.resize(value.len() + 32, 0);
+        }
+        (key(oid, 3), value)
+    }
+
+    fn drec(dir: u64, name: &str, ino: u64, file_type: u16) -> (Vec<u8>, Vec<u8>) {
+        let mut key = key(dir, 9);
+        key.extend_from_slice(&((name.len() as u32 + 1) | (0xabcd << 10)).to_le_bytes());
+        key.extend_from_slice(name.as_bytes());
+        key.push(0);
+        let mut value = ino.to_le_bytes().to_vec();
+        value.extend_from_slice(&[0; 8]);
+        value.extend_from_slice(&file_type.to_le_bytes());
+        (key, value)
+    }
+
+    fn xattr(oid: u64, name: &str, data: &[u8]) -> (Vec<u8>, Vec<u8>) {
+        let mut key = key(oid, 4);
+        key.extend_from_slice(&(name.len() as u16 + 1).to_le_bytes());
+        key.extend_from_slice(name.as_bytes());
+        key.push(0);
+        let mut value = 2u16.to_le_bytes().to_vec();
+        value.extend_from_slice(&(data.len() as u16).to_le_bytes());
+        value.extend_from_slice(data);
+        (key, value)
+    }
+
+    /// A container of one case-insensitive System volume:
+    ///   /hello, /link -> System/version.plist, and the plist compressed
+    fn image() -> Vec<u8> {
+        let mut image = vec![0u8; 8 * BLOCK];
+
+        let mut nx = vec![0u8; 200];
+        nx[0..4].copy_from_slice(b"NXSB");
+        nx[4..8].copy_from_slice(&(BLOCK as u32).to_le_bytes());
+        nx[8..16].copy_from_slice(&8u64.to_le_bytes());
+        nx[32..40].copy_from_slice(&0x2u64.to_le_bytes());
+        nx[40..56].copy_from_slice(&[0x42; 16]);
+        nx[128..136].copy_from_slice(&1u64.to_le_bytes());
+        nx[148..152].copy_from_slice(&1u32.to_le_bytes());
+        nx[152..160].copy_from_slice(&VOLUME_OID.to_le_bytes());
+        object(&mut image, 0, 1, 0x8000_0001, &nx);
+        object(&mut image, 1, 1, 0x4000_000b, &omap(2));
+        let entries = [omap_entry(VOLUME_OID, 3)];
+        object(&mut image, 2, 2, 0x4000_0002, &leaf(&entries, true));
+
+        let mut apsb = vec![0u8; 934];
+        apsb[0..4].copy_from_slice(b"APSB");
+        apsb[24..32].copy_from_slice(&0x1u64.to_le_bytes());
+        apsb[84..88].copy_from_slice(&0x2u32.to_le_bytes());
+        apsb[96..104].copy_from_slice(&4u64.to_le_bytes());
+        apsb[104..112].copy_from_slice(&FS_TREE_OID.to_le_bytes());
+        apsb[208..224].copy_from_slice(&[0x24; 16]);
+        apsb[232..240].copy_from_slice(&0x1u64.to_le_bytes());
+        apsb[672..684].copy_from_slice(b"Macintosh HD");
+        apsb[932..934].copy_from_slice(&0x1u16.to_le_bytes());
+        object(&mut image, 3, VOLUME_OID, 0xd, &apsb);
+        object(&mut image, 4, 4, 0x4000_000b, &omap(5));
+        let entries = [omap_entry(FS_TREE_OID, 6)];
+        object(&mut image, 5, 5, 0x4000_0002, &leaf(&entries, true));
+
+        let mut header = b"fpmc".to_vec();
+        header.extend_from_slice(&1u32.to_le_bytes());
+        header.extend_from_slice(&8u64.to_le_bytes());
+        header.extend_from_slice(b"<plist/>");
+        let mut extent = key(HELLO, 8);
+        extent.extend_from_slice(&0u64.to_le_bytes());
+        let mut extent_value = (BLOCK as u64).to_le_bytes().to_vec();
+        extent_value.extend_from_slice(&7u64.to_le_bytes());
+        extent_value.extend_from_slice(&[0; 8]);
+        let records = [
+            inode(2, 1, 0o40755, 0, None),
+            drec(2, "System", SYSTEM, 4),
+            drec(2, "hello", HELLO, 8),
+            drec(2, "link", LINK, 10),
+            inode(SYSTEM, 2, 0o40755, 0, None),
+            drec(SYSTEM, "version.plist", PLIST, 8),
+            inode(HELLO, 2, 0o100644, 0, Some(6)),
+            (extent, extent_value),
+            inode(LINK, 2, 0o120755, 0, None),
+            xattr(LINK, "com.apple.fs.symlink", b"System/version.plist\0"),
+            inode(PLIST, SYSTEM, 0o100644, 0x20, None),
+            xattr(PLIST, "com.apple.decmpfs", &header),
+        ];
+        object(&mut image, 6, FS_TREE_OID, 0x2, &leaf(&records, false));
+        image[7 * BLOCK..7 * BLOCK + 6].copy_from_slice(b"hello\n");
+        image
+    }
+
+    #[test]
+    fn test_read_volume() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&image()).unwrap();
+        let apfs = Apfs::open(file.path(), 0).unwrap();
+        assert_eq!(apfs.uuid(), Uuid::from_bytes([0x42; 16]));
+        assert_eq!(
+            apfs.volumes().unwrap(),
+            [ApfsVolume {
+                index: 0,
+                name: "Macintosh HD".to_string(),
+                role: Some("System".to_string()),
+                uuid: Uuid::from_bytes([0x24; 16]),
+                encrypted: false,
+            }]
+        );
+
+        let fs = apfs.volume(0).unwrap();
+        let names: Vec<_> = fs
+            .list_dir("/")
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.name, entry.ino, entry.file_type))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("System".to_string(), SYSTEM, 4),
+                ("hello".to_string(), HELLO, 8),
+                ("link".to_string(), LINK, 10),
+            ]
+        );
+        assert_eq!(fs.read_file("/hello").unwrap(), b"hello\n");
+        assert_eq!(fs.read_file("/System/../HELLO").unwrap(), b"hello\n");
+        assert_eq!(fs.read_file("/link").unwrap(), b"<plist/>");
+        assert_eq!(fs.read_link("/link").unwrap(), "System/version.plist");
+
+        let stat = fs.stat("/hello").unwrap();
+        assert_eq!((stat.mode, stat.uid, stat.size), (0o100644, 501, 6));
+        assert_eq!(stat.mtime, 1_700_000_000);
+        assert_eq!(fs.stat("/System/version.plist").unwrap().size, 8);
+        assert!(!fs.exists("/nope").unwrap());
+        assert!(matches!(
+            fs.read_file("/System"),
+            Err(Error::InvalidOperation(_))
+        ));
+    }
+}
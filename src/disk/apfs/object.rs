@@ -0,0 +1,178 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Objects, their checksums and the container superblock
+
+use crate::core::{Error, Result};
+
+/// Every object starts with a checksum, its id, transaction and type
+pub(super) const OBJECT_HEADER_SIZE: usize = 32;
+
+pub(super) const TYPE_NX_SUPERBLOCK: u32 = 0x1;
+pub(super) const TYPE_BTREE: u32 = 0x2;
+pub(super) const TYPE_BTREE_NODE: u32 = 0x3;
+pub(super) const TYPE_OMAP: u32 = 0xb;
+pub(super) const TYPE_FS: u32 = 0xd;
+const TYPE_MASK: u32 = 0xffff;
+
+/// Objects of trees with this storage type are at their id, not mapped
+pub(super) const OBJ_PHYSICAL: u32 = 0x4000_0000;
+const OBJ_STORAGETYPE_MASK: u32 = 0xc000_0000;
+
+const NX_MAGIC: &[u8; 4] = b"NXSB";
+const NX_MIN_BLOCK_SIZE: u64 = 4096;
+const NX_MAX_BLOCK_SIZE: u64 = 65536;
+const NX_MAX_FILE_SYSTEMS: usize = 100;
+/// The checkpoint descriptor area is a tree rather than contiguous
+const XP_DESC_TREE: u32 = 0x8000_0000;
+
+const NX_INCOMPAT_VERSION1: u64 = 0x1;
+const NX_INCOMPAT_VERSION2: u64 = 0x2;
+const NX_INCOMPAT_FUSION: u64 = 0x100;
+
+pub(super) fn le_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes([buf[offset], buf[offset + 1]])
+}
+
+pub(super) fn le_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+pub(super) fn le_u64(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
+
+/// Fletcher-64 over the object past its checksum field
+pub(super) fn fletcher64(object: &[u8]) -> u64 {
+    const MOD: u64 = 0xffff_ffff;
+    let (sum1, sum2) = object[8..]
+        .chunks_exact(4)
+        .fold((0u64, 0u64), |(sum1, sum2), word| {
+            let sum1 = (sum1 + u64::from(le_u32(word, 0))) % MOD;
+            (sum1, (sum2 + sum1) % MOD)
+        });
+    let low = MOD - ((sum1 + sum2) % MOD);
+    let high = MOD - ((sum1 + low) % MOD);
+    (high << 32) | low
+}
+
+pub(super) fn checksum_matches(object: &[u8]) -> bool {
+    object.len() > OBJECT_HEADER_SIZE && fletcher64(object) == le_u64(object, 0)
+}
+
+/// Type of an object, without its storage and other flags
+pub(super) fn object_type(object: &[u8]) -> u32 {
+    le_u32(object, 24) & TYPE_MASK
+}
+
+/// Transaction that wrote an object
+pub(super) fn object_xid(object: &[u8]) -> u64 {
+    le_u64(object, 16)
+}
+
+/// Whether the objects of a tree of this type are physical
+pub(super) fn is_physical(tree_type: u32) -> bool {
+    tree_type & OBJ_STORAGETYPE_MASK == OBJ_PHYSICAL
+}
+
+/// Check the checksum and type of object `oid`
+pub(super) fn verify(object: &[u8], oid: u64, kind: u32) -> Result<()> {
+    if !checksum_matches(object) {
+        return Err(Error::InvalidFormat(format!(
+            "APFS object {}: checksum mismatch",
+            oid
+        )));
+    }
+    if object_type(object) != kind {
+        return Err(Error::InvalidFormat(format!(
+            "APFS object {}: type 0x{:x}, expected 0x{:x}",
+            oid,
+            object_type(object),
+            kind
+        )));
+    }
+    Ok(())
+}
+
+pub(super) struct Container {
+    pub block_size: u64,
+    pub block_count: u64,
+    pub uuid: [u8; 16],
+    /// Transaction of the checkpoint read
+    pub xid: u64,
+    pub omap_oid: u64,
+    /// Volume superblocks, virtual objects
+    pub fs_oids: Vec<u64>,
+    xp_desc_base: u64,
+    xp_desc_blocks: u32,
+}
+
+impl Container {
+    /// Block size from the first sector of a container superblock
+    pub fn block_size(head: &[u8]) -> Result<u64> {
+        if &head[32..36] != NX_MAGIC {
+            return Err(Error::InvalidFormat("not an APFS container".to_string()));
+        }
+        let block_size = u64::from(le_u32(head, 36));
+        if !block_size.is_power_of_two()
+            || !(NX_MIN_BLOCK_SIZE..=NX_MAX_BLOCK_SIZE).contains(&block_size)
+        {
+            return Err(Error::InvalidFormat(format!(
+                "APFS block size {}",
+                block_size
+            )));
+        }
+        Ok(block_size)
+    }
+
+    pub fn parse(raw: &[u8]) -> Result<Self> {
+        if &raw[32..36] != NX_MAGIC
+            || object_type(raw) != TYPE_NX_SUPERBLOCK
+            || !checksum_matches(raw)
+        {
+            return Err(Error::InvalidFormat(
+                "corrupt APFS container superblock".to_string(),
+            ));
+        }
+        let incompat = le_u64(raw, 64);
+        if incompat & NX_INCOMPAT_VERSION2 == 0 || incompat & NX_INCOMPAT_VERSION1 != 0 {
+            return Err(Error::Unsupported(
+                "APFS containers of pre-release versions".to_string(),
+            ));
+        }
+        if incompat & NX_INCOMPAT_FUSION != 0 {
+            return Err(Error::Unsupported("Fusion APFS containers".to_string()));
+        }
+        let max_fs = (le_u32(raw, 180) as usize).min(NX_MAX_FILE_SYSTEMS);
+        Ok(Self {
+            block_size: u64::from(le_u32(raw, 36)),
+            block_count: le_u64(raw, 40),
+            uuid: raw[72..88].try_into().unwrap(),
+            xid: object_xid(raw),
+            omap_oid: le_u64(raw, 160),
+            fs_oids: (0..max_fs)
+                .map(|i| le_u64(raw, 184 + i * 8))
+                .filter(|&oid| oid != 0)
+                .collect(),
+            xp_desc_base: le_u64(raw, 112),
+            xp_desc_blocks: le_u32(raw, 104),
+        })
+    }
+
+    /// Blocks of the checkpoint descriptor area, where later copies of the
+    /// superblock are, unless the area is a tree
+    pub fn checkpoint_blocks(&self) -> impl Iterator<Item = u64> + '_ {
+        let count = if self.xp_desc_blocks & XP_DESC_TREE != 0 {
+            0
+        } else {
+            u64::from(self.xp_desc_blocks)
+        };
+        (0..count).map(move |i| self.xp_desc_base + i)
+    }
+
+    /// Whether a checkpoint copy of the superblock is newer than this one
+    pub fn is_newer(copy: &[u8], than: u64) -> bool {
+        &copy[32..36] == NX_MAGIC
+            && object_type(copy) == TYPE_NX_SUPERBLOCK
+            && checksum_matches(copy)
+            && object_xid(copy) > than
+    }
+}
@@ -0,0 +1,461 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! BIOS/MBR and UEFI/GPT boot layouts
+//!
+//! Cloud targets boot guests either with BIOS or with UEFI firmware. A
+//! BIOS guest boots GRUB from the gap after the MBR, a UEFI guest boots
+//! shim or GRUB from an EFI system partition (ESP), normally on a GPT disk.
+//!
+//! [`BootLayout::analyze`] reads which of the two an image has, and
+//! [`BootLayout::conversion_plan`] works out the partition table changes a
+//! conversion makes. Installing the bootloader happens in the guest, see
+//! `Guestfs::install_uefi_boot` and `Guestfs::install_bios_boot`.
+
+use crate::core::{Error, Result};
+use crate::disk::alignment::{ALIGNMENT, GPT_BACKUP_SIZE, MBR_EXTENDED};
+use crate::disk::partition::{PartitionTable, PartitionType};
+use crate::disk::reader::DiskReader;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::str::FromStr;
+
+/// GPT type of an EFI system partition
+pub const ESP_TYPE_GUID: &str = "c12a7328-f81f-11d2-ba4b-00a0c93ec93b";
+/// GPT type of the partition GRUB embeds its core image in on BIOS/GPT disks
+pub const BIOS_BOOT_TYPE_GUID: &str = "21686148-6449-6e6f-744e-656564454649";
+/// MBR type of an EFI system partition
+pub const ESP_MBR_TYPE: u8 = 0xef;
+/// Size of a created ESP; FAT32 with 4 KiB sectors needs 260 MiB
+pub const ESP_SIZE: u64 = 260 * 1024 * 1024;
+
+/// Sectors the primary GPT needs after the MBR
+const GPT_PRIMARY_SIZE: u64 = 34 * 512;
+/// Largest disk offset an MBR can address with 512-byte sectors
+const MBR_LIMIT: u64 = (u32::MAX as u64) * 512;
+
+/// Firmware a guest boots with
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum BootMode {
+    Bios,
+    Uefi,
+}
+
+impl fmt::Display for BootMode {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BootMode::Bios => write!(f, "bios"),
+            BootMode::Uefi => write!(f, "uefi"),
+        }
+    }
+}
+
+impl FromStr for BootMode {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.to_lowercase().as_str() {
+            "bios" | "legacy" => Ok(BootMode::Bios),
+            "uefi" | "efi" => Ok(BootMode::Uefi),
+            other => Err(Error::InvalidFormat(format!(
+                "Unknown boot mode '{}' (bios, uefi)",
+                other
+            ))),
+        }
+    }
+}
+
+/// A partition as far as booting is concerned
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BootPartition {
+    pub number: u32,
+    /// Start offset in bytes
+    pub start: u64,
+    /// Size in bytes
+    pub size: u64,
+    /// EFI system partition
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub esp: bool,
+    /// GRUB BIOS boot partition
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub bios_boot: bool,
+    /// MBR extended partition holding logical partitions
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub extended: bool,
+}
+
+impl BootPartition {
+    pub fn end(&self) -> u64 {
+        self.start + self.size
+    }
+}
+
+/// Partition layout of a disk image
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BootLayout {
+    /// gpt or msdos
+    pub table_type: String,
+    /// Disk size in bytes
+    pub disk_size: u64,
+    pub partitions: Vec<BootPartition>,
+}
+
+/// A partition a conversion creates
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct NewPartition {
+    pub number: u32,
+    /// Start offset in bytes
+    pub start: u64,
+    /// Size in bytes
+    pub size: u64,
+}
+
+/// Partition table changes of a boot mode conversion
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BootConversionPlan {
+    pub target: BootMode,
+    /// Partition table before and after the conversion
+    pub from_table: String,
+    pub to_table: String,
+    /// Bytes the image grows by to make room for the ESP
+    pub grow: u64,
+    /// ESP to create and format
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub new_esp: Option<NewPartition>,
+    /// GPT partitions that go into the MBR, in MBR order
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub mbr_partitions: Vec<u32>,
+    /// MBR partition marked active for BIOSes that look for one
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub active: Option<u32>,
+}
+
+impl BootConversionPlan {
+    /// Partitions whose number changes: (old, new)
+    pub fn renumbered(&self) -> Vec<(u32, u32)> {
+        self.mbr_partitions
+            .iter()
+            .zip(1..)
+            .filter(|(old, new)| *old != new)
+            .map(|(old, new)| (*old, new))
+            .collect()
+    }
+}
+
+impl BootLayout {
+    /// Read the partition layout of a disk image or block device
+    pub fn analyze(reader: &mut DiskReader) -> Result<Self> {
+        let table = PartitionTable::parse(reader)?;
+        let table_type = match table.table_type() {
+            PartitionType::MBR => "msdos",
+            PartitionType::GPT => "gpt",
+            PartitionType::Unknown => {
+                return Err(Error::Unsupported(
+                    "disks without a partition table".to_string(),
+                ))
+            }
+        };
+
+        let partitions = table
+            .partitions()
+            .iter()
+            .map(|partition| {
+                let type_guid = partition.type_guid.as_deref().map(str::to_lowercase);
+                BootPartition {
+                    number: partition.number,
+                    start: partition.start_lba * 512,
+                    size: partition.size_sectors * 512,
+                    esp: type_guid.as_deref() == Some(ESP_TYPE_GUID)
+                        || (type_guid.is_none() && partition.type_id == ESP_MBR_TYPE),
+                    bios_boot: type_guid.as_deref() == Some(BIOS_BOOT_TYPE_GUID),
+                    extended: type_guid.is_none() && MBR_EXTENDED.contains(&partition.type_id),
+                }
+            })
+            .collect();
+
+        Ok(Self {
+            table_type: table_type.to_string(),
+            disk_size: reader.size(),
+            partitions,
+        })
+    }
+
+    /// Boot mode the layout is made for: UEFI if it has an ESP
+    pub fn mode(&self) -> BootMode {
+        if self.esp().is_some() {
+            BootMode::Uefi
+        } else {
+            BootMode::Bios
+        }
+    }
+
+    pub fn esp(&self) -> Option<&BootPartition> {
+        self.partitions.iter().find(|partition| partition.esp)
+    }
+
+    /// Partition table changes that make the layout boot with `target`
+    ///
+    /// To UEFI, an MBR disk becomes GPT and an ESP is added after the last
+    /// partition, growing the image if there is no room. To BIOS, a GPT
+    /// disk becomes MBR unless it has a BIOS boot partition for GRUB; the
+    /// ESP is kept.
+    pub fn conversion_plan(&self, target: BootMode) -> Result<BootConversionPlan> {
+        if self.mode() == target {
+            return Err(Error::InvalidOperation(format!(
+                "the image is already laid out for {}",
+                target
+            )));
+        }
+
+        let mut plan = BootConversionPlan {
+            target,
+            from_table: self.table_type.clone(),
+            to_table: self.table_type.clone(),
+            grow: 0,
+            new_esp: None,
+            mbr_partitions: Vec::new(),
+            active: None,
+        };
+        let first_start = self
+            .partitions
+            .iter()
+            .map(|partition| partition.start)
+            .min()
+            .unwrap_or(ALIGNMENT);
+
+        match target {
+            BootMode::Uefi => {
+                if self.table_type == "msdos" {
+                    if first_start < GPT_PRIMARY_SIZE {
+                        return Err(Error::InvalidOperation(format!(
+                            "the first partition starts at byte {}, where the GPT would go",
+                            first_start
+                        )));
+                    }
+                    plan.to_table = "gpt".to_string();
+                }
+
+                let last_end = self
+                    .partitions
+                    .iter()
+                    .map(BootPartition::end)
+                    .max()
+                    .unwrap_or(0);
+                let start = last_end.next_multiple_of(ALIGNMENT);
+                let needed = start + ESP_SIZE + GPT_BACKUP_SIZE;
+                plan.grow = needed
+                    .saturating_sub(self.disk_size)
+                    .next_multiple_of(ALIGNMENT);
+                plan.new_esp = Some(NewPartition {
+                    number: self.partitions.iter().map(|p| p.number).max().unwrap_or(0) + 1,
+                    start,
+                    size: ESP_SIZE,
+                });
+            }
+            BootMode::Bios => {
+                let bios_boot = self.partitions.iter().any(|partition| partition.bios_boot);
+                // GRUB embeds its core image between the MBR and the first partition
+                if !bios_boot && first_start < ALIGNMENT {
+                    return Err(Error::InvalidOperation(format!(
+                        "the first partition starts at byte {}, leaving no room for GRUB \
+                         before it",
+                        first_start
+                    )));
+                }
+                if self.table_type == "gpt" && !bios_boot {
+                    let mut partitions: Vec<&BootPartition> = self.partitions.iter().collect();
+                    partitions.sort_by_key(|partition| partition.number);
+                    if partitions.len() > 4 {
+                        return Err(Error::InvalidOperation(format!(
+                            "an MBR holds 4 partitions, the image has {}",
+                            partitions.len()
+                        )));
+                    }
+                    if let Some(beyond) = partitions.iter().find(|p| p.end() > MBR_LIMIT) {
+                        return Err(Error::InvalidOperation(format!(
+                            "partition {} ends beyond the 2 TiB an MBR can address",
+                            beyond.number
+                        )));
+                    }
+                    plan.to_table = "msdos".to_string();
+                    plan.mbr_partitions = partitions.iter().map(|p| p.number).collect();
+                    plan.active = partitions
+                        .iter()
+                        .position(|partition| !partition.esp)
+                        .map(|index| index as u32 + 1);
+                } else if self.table_type == "msdos" {
+                    plan.active = self
+                        .partitions
+                        .iter()
+                        .filter(|partition| !partition.esp && !partition.extended)
+                        .map(|partition| partition.number)
+                        .min();
+                }
+            }
+        }
+
+        Ok(plan)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::FileExt;
+
+    const MIB: u64 = 1024 * 1024;
+
+    fn partition(number: u32, start_mib: u64, size_mib: u64) -> BootPartition {
+        BootPartition {
+            number,
+            start: start_mib * MIB,
+            size: size_mib * MIB,
+            esp: false,
+            bios_boot: false,
+            extended: false,
+        }
+    }
+
+    fn esp(number: u32, start_mib: u64, size_mib: u64) -> BootPartition {
+        BootPartition {
+            esp: true,
+            ..partition(number, start_mib, size_mib)
+        }
+    }
+
+    fn layout(table_type: &str, disk_mib: u64, partitions: Vec<BootPartition>) -> BootLayout {
+        BootLayout {
+            table_type: table_type.to_string(),
+            disk_size: disk_mib * MIB,
+            partitions,
+        }
+    }
+
+    #[test]
+    fn test_analyze() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("bios.img");
+        let mut mbr = vec![0u8; 512];
+        for (i, (start, sectors, type_id)) in [(2048u32, 2048u32, 0xefu8), (4096, 8192, 0x83)]
+            .iter()
+            .enumerate()
+        {
+            let entry = 446 + i * 16;
+            mbr[entry + 4] = *type_id;
+            mbr[entry + 8..entry + 12].copy_from_slice(&start.to_le_bytes());
+            mbr[entry + 12..entry + 16].copy_from_slice(&sectors.to_le_bytes());
+        }
+        mbr[510] = 0x55;
+        mbr[511] = 0xaa;
+        let file = std::fs::File::create(&path).unwrap();
+        file.set_len(8 * MIB).unwrap();
+        file.write_all_at(&mbr, 0).unwrap();
+
+        let layout = BootLayout::analyze(&mut DiskReader::open(&path).unwrap()).unwrap();
+        assert_eq!(layout.table_type, "msdos");
+        assert_eq!(layout.disk_size, 8 * MIB);
+        assert_eq!(layout.partitions, vec![esp(1, 1, 1), partition(2, 2, 4)]);
+        assert_eq!(layout.mode(), BootMode::Uefi);
+    }
+
+    #[test]
+    fn test_plan_to_uefi() {
+        // No room after the last partition: the image grows
+        let bios = layout(
+            "msdos",
+            10240,
+            vec![partition(1, 1, 1024), partition(2, 1025, 9215)],
+        );
+        assert_eq!(bios.mode(), BootMode::Bios);
+        let plan = bios.conversion_plan(BootMode::Uefi).unwrap();
+        assert_eq!(plan.to_table, "gpt");
+        assert_eq!(plan.grow, 261 * MIB);
+        assert_eq!(
+            plan.new_esp,
+            Some(NewPartition {
+                number: 3,
+                start: 10240 * MIB,
+                size: ESP_SIZE,
+            })
+        );
+
+        // Free space at the end is used
+        let roomy = layout("msdos", 20480, vec![partition(1, 1, 1024)]);
+        let plan = roomy.conversion_plan(BootMode::Uefi).unwrap();
+        assert_eq!(plan.grow, 0);
+        assert_eq!(plan.new_esp.unwrap().start, 1025 * MIB);
+
+        let cramped = BootLayout {
+            partitions: vec![BootPartition {
+                start: 63 * 512,
+                ..partition(1, 0, 1024)
+            }],
+            ..roomy.clone()
+        };
+        assert!(cramped.conversion_plan(BootMode::Uefi).is_ok());
+        let cramped = BootLayout {
+            partitions: vec![BootPartition {
+                start: 512,
+                ..partition(1, 0, 1024)
+            }],
+            ..roomy
+        };
+        assert!(cramped.conversion_plan(BootMode::Uefi).is_err());
+
+        assert!(bios.conversion_plan(BootMode::Bios).is_err());
+    }
+
+    #[test]
+    fn test_plan_to_bios() {
+        let uefi = layout(
+            "gpt",
+            10240,
+            vec![
+                esp(1, 1, 600),
+                partition(2, 601, 1024),
+                partition(3, 1625, 8000),
+            ],
+        );
+        let plan = uefi.conversion_plan(BootMode::Bios).unwrap();
+        assert_eq!(plan.to_table, "msdos");
+        assert_eq!(plan.mbr_partitions, vec![1, 2, 3]);
+        assert_eq!(plan.active, Some(2));
+        assert!(plan.renumbered().is_empty());
+
+        // Ubuntu cloud images: root 1, BIOS boot 14, ESP 15
+        let mut hybrid = layout("gpt", 10240, vec![partition(1, 111, 9000), esp(15, 5, 106)]);
+        let plan = hybrid.conversion_plan(BootMode::Bios).unwrap();
+        assert_eq!(plan.mbr_partitions, vec![1, 15]);
+        assert_eq!(plan.active, Some(1));
+        assert_eq!(plan.renumbered(), vec![(15, 2)]);
+
+        let mut msdos = layout(
+            "msdos",
+            10240,
+            vec![esp(1, 1, 512), partition(2, 513, 9000)],
+        );
+        assert_eq!(
+            msdos.conversion_plan(BootMode::Bios).unwrap().active,
+            Some(2)
+        );
+        msdos.partitions[0].start = 63 * 512;
+        assert!(msdos.conversion_plan(BootMode::Bios).is_err());
+
+        hybrid.partitions.push(BootPartition {
+            bios_boot: true,
+            ..partition(14, 1, 4)
+        });
+        let plan = hybrid.conversion_plan(BootMode::Bios).unwrap();
+        assert_eq!(plan.to_table, "gpt");
+        assert!(plan.mbr_partitions.is_empty());
+
+        let crowded = layout(
+            "gpt",
+            10240,
+            (1..=5)
+                .map(|n| partition(n, n as u64 * 100, 100))
+                .chain([esp(6, 700, 100)])
+                .collect(),
+        );
+        assert!(crowded.conversion_plan(BootMode::Bios).is_err());
+    }
+}
@@ -0,0 +1,335 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Transparent file compression of macOS (decmpfs)
+//!
+//! Since Mac OS X 10.6 many system files are stored compressed, on both
+//! HFS+ and APFS: the file is empty, has the `UF_COMPRESSED` flag, and a
+//! `com.apple.decmpfs` extended attribute holds a header and either the
+//! compressed data or, for larger files, the type of the compressed data
+//! in the resource fork. zlib and LZVN compression are read; LZFSE, which
+//! newer releases use for some files, is not.
+
+use crate::core::{Error, Result};
+use flate2::read::ZlibDecoder;
+use std::io::Read;
+
+/// Extended attribute with the compression header
+pub(super) const XATTR: &str = "com.apple.decmpfs";
+/// Extended attribute holding the resource fork on APFS
+pub(super) const RESOURCE_FORK_XATTR: &str = "com.apple.ResourceFork";
+/// BSD flag of compressed files
+pub(super) const UF_COMPRESSED: u32 = 0x20;
+
+/// "cmpf", little-endian
+const MAGIC: u32 = 0x636d_7066;
+const HEADER_SIZE: usize = 16;
+
+/// Uncompressed data in the attribute
+const TYPE_UNCOMPRESSED: u32 = 1;
+const TYPE_ZLIB: u32 = 3;
+const TYPE_ZLIB_RSRC: u32 = 4;
+const TYPE_LZVN: u32 = 7;
+const TYPE_LZVN_RSRC: u32 = 8;
+const TYPE_LZFSE: u32 = 11;
+const TYPE_LZFSE_RSRC: u32 = 12;
+
+/// Resource fork data is compressed in blocks of this size
+const BLOCK_SIZE: usize = 64 * 1024;
+
+/// Files larger than this are refused rather than decompressed in memory
+const MAX_SIZE: u64 = 1 << 32;
+
+fn le_u32(buf: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        buf.get(offset..offset + 4)?.try_into().unwrap(),
+    ))
+}
+
+fn be_u32(buf: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(
+        buf.get(offset..offset + 4)?.try_into().unwrap(),
+    ))
+}
+
+fn corrupt(what: &str) -> Error {
+    Error::InvalidFormat(format!("corrupt compressed file: {}", what))
+}
+
+/// Size of a compressed file from its `com.apple.decmpfs` attribute
+pub(super) fn uncompressed_size(xattr: &[u8]) -> Option<u64> {
+    if le_u32(xattr, 0) != Some(MAGIC) {
+        return None;
+    }
+    Some(u64::from_le_bytes(xattr.get(8..16)?.try_into().unwrap()))
+}
+
+/// Content of a compressed file from its `com.apple.decmpfs` attribute,
+/// reading the resource fork if the data is there
+pub(super) fn decompress(
+    xattr: &[u8],
+    resource_fork: impl FnOnce() -> Result<Vec<u8>>,
+) -> Result<Vec<u8>> {
+    if xattr.len() < HEADER_SIZE || le_u32(xattr, 0) != Some(MAGIC) {
+        return Err(corrupt("bad header"));
+    }
+    let kind = le_u32(xattr, 4).unwrap();
+    let size = u64::from_le_bytes(xattr[8..16].try_into().unwrap());
+    if size > MAX_SIZE {
+        return Err(Error::ResourceLimit(format!(
+            "compressed file of {} bytes",
+            size
+        )));
+    }
+    let size = size as usize;
+    let inline = &xattr[HEADER_SIZE..];
+
+    let data = match kind {
+        TYPE_UNCOMPRESSED => inline.to_vec(),
+        TYPE_ZLIB => zlib_block(inline, size)?,
+        TYPE_LZVN => lzvn_block(inline, size)?,
+        TYPE_ZLIB_RSRC => {
+            let fork = resource_fork()?;
+            zlib_resource_fork(&fork, size)?
+        }
+        TYPE_LZVN_RSRC => {
+            let fork = resource_fork()?;
+            lzvn_resource_fork(&fork, size)?
+        }
+        TYPE_LZFSE | TYPE_LZFSE_RSRC => {
+            return Err(Error::Unsupported(
+                "files compressed with LZFSE".to_string(),
+            ))
+        }
+        kind => {
+            return Err(Error::Unsupported(format!(
+                "decmpfs compression type {}",
+                kind
+            )))
+        }
+    };
+    if data.len() != size {
+        return Err(corrupt("size mismatch"));
+    }
+    Ok(data)
+}
+
+/// A zlib block; one whose low nibble of the first byte is 0xf is stored
+fn zlib_block(block: &[u8], max: usize) -> Result<Vec<u8>> {
+    match block.first() {
+        Some(&first) if first & 0x0f == 0x0f => Ok(block[1..].to_vec()),
+        _ => {
+            let mut data = Vec::with_capacity(max.min(BLOCK_SIZE));
+            ZlibDecoder::new(block)
+                .take(max as u64 + 1)
+                .read_to_end(&mut data)
+                .map_err(|e| corrupt(&e.to_string()))?;
+            Ok(data)
+        }
+    }
+}
+
+/// An LZVN block; one starting with the end of stream opcode is stored
+fn lzvn_block(block: &[u8], max: usize) -> Result<Vec<u8>> {
+    match block.first() {
+        Some(&LZVN_EOS) => Ok(block[1..].to_vec()),
+        _ => lzvn_decode(block, max),
+    }
+}
+
+/// zlib blocks in a resource fork: a resource fork header, then a single
+/// resource with a table of block offsets and sizes
+fn zlib_resource_fork(fork: &[u8], size: usize) -> Result<Vec<u8>> {
+    let data_offset = be_u32(fork, 0).ok_or_else(|| corrupt("resource fork"))? as usize;
+    // The resource length, then the table
+    let table = data_offset + 4;
+    let count = le_u32(fork, table).ok_or_else(|| corrupt("resource fork"))? as usize;
+    if count > size / BLOCK_SIZE + 1 {
+        return Err(corrupt("block count"));
+    }
+    let mut data = Vec::with_capacity(size);
+    for i in 0..count {
+        let entry = table + 4 + i * 8;
+        let (offset, len) = le_u32(fork, entry)
+            .zip(le_u32(fork, entry + 4))
+            .ok_or_else(|| corrupt("block table"))?;
+        let start = table + offset as usize;
+        let block = fork
+            .get(start..start + len as usize)
+            .ok_or_else(|| corrupt("block past the resource fork"))?;
+        data.extend(zlib_block(block, BLOCK_SIZE)?);
+    }
+    Ok(data)
+}
+
+/// LZVN blocks in a resource fork, after a table of their offsets
+fn lzvn_resource_fork(fork: &[u8], size: usize) -> Result<Vec<u8>> {
+    let first = le_u32(fork, 0).ok_or_else(|| corrupt("resource fork"))? as usize;
+    if first < 8 || !first.is_multiple_of(4) || first / 4 - 1 > size / BLOCK_SIZE + 1 {
+        return Err(corrupt("block table"));
+    }
+    let mut data = Vec::with_capacity(size);
+    for i in 0..first / 4 - 1 {
+        let (start, end) = le_u32(fork, i * 4)
+            .zip(le_u32(fork, i * 4 + 4))
+            .ok_or_else(|| corrupt("block table"))?;
+        let block = fork
+            .get(start as usize..end as usize)
+            .ok_or_else(|| corrupt("block past the resource fork"))?;
+        data.extend(lzvn_block(block, BLOCK_SIZE)?);
+    }
+    Ok(data)
+}
+
+const LZVN_EOS: u8 = 0x06;
+
+/// Decode an LZVN stream of at most `max` bytes
+///
+/// Each opcode copies literals from the stream, then a match from the
+/// output: at a distance it encodes or the previous one.
+fn lzvn_decode(src: &[u8], max: usize) -> Result<Vec<u8>> {
+    let mut out: Vec<u8> = Vec::with_capacity(max.min(BLOCK_SIZE));
+    let mut pos = 0;
+    let mut distance = 0usize;
+    let byte = |at: usize| -> Result<usize> {
+        src.get(at)
+            .map(|&b| usize::from(b))
+            .ok_or_else(|| corrupt("truncated LZVN stream"))
+    };
+
+    loop {
+        let opc = byte(pos)?;
+        // Literal count, match length and the opcode's length
+        let (literals, matched, len) = match opc {
+            0x06 => break,
+            0x0e | 0x16 => (0, 0, 1),
+            0x1e | 0x26 | 0x2e | 0x36 | 0x3e | 0x70..=0x7f | 0xd0..=0xdf => {
+                return Err(corrupt("undefined LZVN opcode"))
+            }
+            // Large literal and small literal
+            0xe0 => (byte(pos + 1)? + 16, 0, 2),
+            0xe1..=0xef => (opc & 0x0f, 0, 1),
+            // Large match and small match, at the previous distance
+            0xf0 => (0, byte(pos + 1)? + 16, 2),
+            0xf1..=0xff => (0, opc & 0x0f, 1),
+            // Medium distance
+            0xa0..=0xbf => {
+                let b1 = byte(pos + 1)?;
+                let b2 = byte(pos + 2)?;
+                distance = (b2 << 6) | (b1 >> 2);
+                ((opc >> 3) & 3, (((opc & 7) << 2) | (b1 & 3)) + 3, 3)
+            }
+            _ => {
+                let literals = opc >> 6;
+                let matched = ((opc >> 3) & 7) + 3;
+                match opc & 7 {
+                    // Previous distance
+                    6 => (literals, matched, 1),
+                    // Large distance
+                    7 => {
+                        distance = byte(pos + 1)? | (byte(pos + 2)? << 8);
+                        (literals, matched, 3)
+                    }
+                    // Small distance
+                    high => {
+                        distance = (high << 8) | byte(pos + 1)?;
+                        (literals, matched, 2)
+                    }
+                }
+            }
+        };
+        pos += len;
+
+        if out.len() + literals + matched > max {
+            return Err(corrupt("LZVN stream longer than the file"));
+        }
+        let literal = src
+            .get(pos..pos + literals)
+            .ok_or_else(|| corrupt("truncated LZVN stream"))?;
+        out.extend_from_slice(literal);
+        pos += literals;
+
+        if matched > 0 {
+            if distance == 0 || distance > out.len() {
+                return Err(corrupt("LZVN match before the start"));
+            }
+            // Matches may overlap what they copy
+            let start = out.len() - distance;
+            for i in 0..matched {
+                out.push(out[start + i]);
+            }
+        }
+    }
+    Ok(out)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    fn header(kind: u32, size: u64) -> Vec<u8> {
+        let mut xattr = b"fpmc".to_vec();
+        xattr.extend_from_slice(&kind.to_le_bytes());
+        xattr.extend_from_slice(&size.to_le_bytes());
+        xattr
+    }
+
+    fn no_fork() -> Result<Vec<u8>> {
+        panic!("the data is in the attribute")
+    }
+
+    #[test]
+    fn test_lzvn() {
+        // "abc" as literals, then a match of 6 at distance 3, then "!"
+        let stream = [0xe3, b'a', b'b', b'c', 0x18, 0x03, 0xe1, b'!', 0x06];
+        let mut xattr = header(TYPE_LZVN, 10);
+        xattr.extend_from_slice(&stream);
+        assert_eq!(decompress(&xattr, no_fork).unwrap(), b"abcabcabc!");
+
+        let mut stored = header(TYPE_LZVN, 3);
+        stored.extend_from_slice(&[0x06, b'x', b'y', b'z']);
+        assert_eq!(decompress(&stored, no_fork).unwrap(), b"xyz");
+
+        let mut bad = header(TYPE_LZVN, 10);
+        bad.extend_from_slice(&[0xf3, 0x06]);
+        assert!(matches!(
+            decompress(&bad, no_fork),
+            Err(Error::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_zlib_resource_fork() {
+        let content = vec![b'z'; BLOCK_SIZE + 100];
+        let mut blocks = Vec::new();
+        for chunk in content.chunks(BLOCK_SIZE) {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(chunk).unwrap();
+            blocks.push(encoder.finish().unwrap());
+        }
+
+        let mut fork = 0x100u32.to_be_bytes().to_vec();
+        fork.resize(0x104, 0);
+        let table = fork.len();
+        fork.extend_from_slice(&(blocks.len() as u32).to_le_bytes());
+        let mut offset = 4 + blocks.len() * 8;
+        for block in &blocks {
+            fork.extend_from_slice(&(offset as u32).to_le_bytes());
+            fork.extend_from_slice(&(block.len() as u32).to_le_bytes());
+            offset += block.len();
+        }
+        assert_eq!(fork.len(), table + 4 + blocks.len() * 8);
+        for block in &blocks {
+            fork.extend_from_slice(block);
+        }
+
+        let xattr = header(TYPE_ZLIB_RSRC, content.len() as u64);
+        assert_eq!(decompress(&xattr, || Ok(fork)).unwrap(), content);
+
+        assert!(matches!(
+            decompress(&header(TYPE_LZFSE, 1), no_fork),
+            Err(Error::Unsupported(_))
+        ));
+    }
+}
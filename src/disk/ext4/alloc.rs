@@ -0,0 +1,222 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Block and inode allocation through the group bitmaps
+
+use super::layout::{BG_BLOCK_UNINIT, BG_INODE_UNINIT};
+use super::{Ext4, Tx};
+use crate::core::{Error, Result};
+
+pub(super) fn bit(bitmap: &[u8], index: u64) -> bool {
+    bitmap[(index / 8) as usize] & (1 << (index % 8)) != 0
+}
+
+fn set_bit(bitmap: &mut [u8], index: u64, value: bool) {
+    let byte = &mut bitmap[(index / 8) as usize];
+    if value {
+        *byte |= 1 << (index % 8);
+    } else {
+        *byte &= !(1 << (index % 8));
+    }
+}
+
+impl Ext4 {
+    pub(super) fn block_group(&self, block: u64) -> u32 {
+        ((block - self.sb.first_data_block) / self.sb.blocks_per_group) as u32
+    }
+
+    pub(super) fn group_start(&self, group: u32) -> u64 {
+        self.sb.first_data_block + u64::from(group) * self.sb.blocks_per_group
+    }
+
+    /// First block of the group holding inode `ino`, where its data goes
+    pub(super) fn inode_goal(&self, ino: u32) -> u64 {
+        self.group_start((ino - 1) / self.sb.inodes_per_group)
+    }
+
+    /// Allocate `count` blocks at or after `goal`, in as few runs as the
+    /// free space allows
+    ///
+    /// Blocks this transaction freed are not reused by it, so they keep
+    /// their contents until it commits.
+    pub(super) fn alloc_blocks(
+        &mut self,
+        tx: &mut Tx,
+        goal: u64,
+        count: u64,
+    ) -> Result<Vec<(u64, u64)>> {
+        if count > self.sb.free_blocks() {
+            return Err(Error::ResourceLimit(format!(
+                "no space left: {} blocks needed, {} free",
+                count,
+                self.sb.free_blocks()
+            )));
+        }
+        let goal = goal.clamp(self.sb.first_data_block, self.sb.blocks_count - 1);
+        let first_group = self.block_group(goal);
+        let mut runs: Vec<(u64, u64)> = Vec::new();
+        let mut left = count;
+        for i in 0..=self.sb.group_count {
+            if left == 0 {
+                break;
+            }
+            let group = (first_group + i) % self.sb.group_count;
+            // Groups whose bitmap was never written have nothing but
+            // metadata allocated; leave them for the kernel to set up
+            if self.groups.free_blocks(group) == 0
+                || (self.sb.uninit_groups() && self.groups.flags(group) & BG_BLOCK_UNINIT != 0)
+            {
+                continue;
+            }
+            let start = self.group_start(group);
+            let blocks = self.sb.group_blocks(group);
+            // The goal's group is scanned from the goal first, then again
+            // from its start on the last round
+            let range = match i {
+                0 => goal - start..blocks,
+                i if i == self.sb.group_count => 0..goal - start,
+                _ => 0..blocks,
+            };
+
+            let bitmap_block = self.groups.block_bitmap(group);
+            let freed = std::mem::take(&mut tx.freed);
+            let bitmap = self.block_mut(tx, bitmap_block)?;
+            let mut taken = 0;
+            let mut index = range.start;
+            while index < range.end && left > 0 {
+                if bit(bitmap, index) || freed.contains(&(start + index)) {
+                    index += 1;
+                    continue;
+                }
+                set_bit(bitmap, index, true);
+                let block = start + index;
+                match runs.last_mut() {
+                    Some((run_start, run_len)) if *run_start + *run_len == block => *run_len += 1,
+                    _ => runs.push((block, 1)),
+                }
+                taken += 1;
+                left -= 1;
+                index += 1;
+            }
+            tx.freed = freed;
+            if taken > 0 {
+                let free = self.groups.free_blocks(group);
+                if taken > free {
+                    return Err(Error::InvalidFormat(format!(
+                        "group {} has more free blocks than it counts",
+                        group
+                    )));
+                }
+                self.groups.set_free_blocks(group, free - taken);
+                tx.block_bitmaps.insert(group);
+                tx.groups.insert(group);
+            }
+        }
+        if left > 0 {
+            return Err(Error::ResourceLimit(format!(
+                "no space left: {} blocks needed",
+                count
+            )));
+        }
+        self.sb.set_free_blocks(self.sb.free_blocks() - count);
+        Ok(runs)
+    }
+
+    pub(super) fn free_blocks(&mut self, tx: &mut Tx, start: u64, len: u64) -> Result<()> {
+        for block in start..start + len {
+            let group = self.block_group(block);
+            let bitmap_block = self.groups.block_bitmap(group);
+            let index = block - self.group_start(group);
+            let bitmap = self.block_mut(tx, bitmap_block)?;
+            if !bit(bitmap, index) {
+                return Err(Error::InvalidFormat(format!(
+                    "block {} is already free",
+                    block
+                )));
+            }
+            set_bit(bitmap, index, false);
+            tx.freed.insert(block);
+            tx.block_bitmaps.insert(group);
+            tx.groups.insert(group);
+            let free = self.groups.free_blocks(group);
+            self.groups.set_free_blocks(group, free + 1);
+        }
+        self.sb.set_free_blocks(self.sb.free_blocks() + len);
+        Ok(())
+    }
+
+    /// Allocate an inode, in the group of `near` if it has room
+    pub(super) fn alloc_inode(&mut self, tx: &mut Tx, near: u32, dir: bool) -> Result<u32> {
+        if self.sb.free_inodes() == 0 {
+            return Err(Error::ResourceLimit("no free inodes left".to_string()));
+        }
+        let per_group = self.sb.inodes_per_group;
+        let first_group = (near - 1) / per_group;
+        for i in 0..self.sb.group_count {
+            let group = (first_group + i) % self.sb.group_count;
+            if self.groups.free_inodes(group) == 0 {
+                continue;
+            }
+            let uninit = self.sb.uninit_groups() && self.groups.flags(group) & BG_INODE_UNINIT != 0;
+            let bitmap_block = self.groups.inode_bitmap(group);
+            let bitmap = self.block_mut(tx, bitmap_block)?;
+            if uninit {
+                // Bits past the inodes of the group stay set
+                let used = (per_group / 8) as usize;
+                bitmap[..used].fill(0);
+                bitmap[used..].fill(0xff);
+            }
+            let Some(index) = (0..u64::from(per_group)).find(|&index| !bit(bitmap, index)) else {
+                continue;
+            };
+            set_bit(bitmap, index, true);
+            let index = index as u32;
+
+            self.groups
+                .set_free_inodes(group, self.groups.free_inodes(group) - 1);
+            if dir {
+                self.groups
+                    .set_used_dirs(group, self.groups.used_dirs(group) + 1);
+            }
+            if self.sb.uninit_groups() {
+                self.groups
+                    .set_flags(group, self.groups.flags(group) & !BG_INODE_UNINIT);
+                // Inodes past the used part of the table may be garbage
+                let unused = self.groups.itable_unused(group);
+                let first_unused = u64::from(per_group).saturating_sub(unused);
+                if u64::from(index) >= first_unused {
+                    self.groups
+                        .set_itable_unused(group, u64::from(per_group - index - 1));
+                }
+            }
+            self.sb.set_free_inodes(self.sb.free_inodes() - 1);
+            tx.inode_bitmaps.insert(group);
+            tx.groups.insert(group);
+            return Ok(group * per_group + index + 1);
+        }
+        Err(Error::ResourceLimit("no free inodes left".to_string()))
+    }
+
+    pub(super) fn free_inode(&mut self, tx: &mut Tx, ino: u32, dir: bool) -> Result<()> {
+        let per_group = self.sb.inodes_per_group;
+        let group = (ino - 1) / per_group;
+        let index = u64::from((ino - 1) % per_group);
+        let bitmap_block = self.groups.inode_bitmap(group);
+        let bitmap = self.block_mut(tx, bitmap_block)?;
+        if !bit(bitmap, index) {
+            return Err(Error::InvalidFormat(format!(
+                "inode {} is already free",
+                ino
+            )));
+        }
+        set_bit(bitmap, index, false);
+        self.groups
+            .set_free_inodes(group, self.groups.free_inodes(group) + 1);
+        if dir {
+            self.groups
+                .set_used_dirs(group, self.groups.used_dirs(group).saturating_sub(1));
+        }
+        self.sb.set_free_inodes(self.sb.free_inodes() + 1);
+        tx.inode_bitmaps.insert(group);
+        tx.groups.insert(group);
+        Ok(())
+    }
+}
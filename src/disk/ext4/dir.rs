@@ -0,0 +1,604 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Directory entries, path lookup and the htree index
+//!
+//! Directories are read block by block, which works for indexed ones too:
+//! index blocks look like blocks of deleted entries. Entries are added to
+//! the leaf the htree hash selects; when that leaf is full the index is
+//! dropped and the directory carries on as a plain linear one, which the
+//! kernel and e2fsck accept.
+
+use super::inode::{Inode, Run, INDEX_FL};
+use super::layout::{crc32c, le_u16, le_u32, put_u16, put_u32, INCOMPAT_FILETYPE};
+use super::{Ext4, Tx};
+use crate::core::{Error, Result};
+
+pub(super) const ROOT_INO: u32 = 2;
+
+/// Directory entry file types
+pub(super) const FT_REG_FILE: u8 = 1;
+pub(super) const FT_DIR: u8 = 2;
+
+/// Checksum entry closing directory blocks of metadata_csum filesystems
+const TAIL_SIZE: usize = 12;
+const TAIL_FILE_TYPE: u8 = 0xde;
+
+const MAX_NAME_LEN: usize = 255;
+/// As Linux's MAXSYMLINKS
+const MAX_SYMLINKS: usize = 40;
+
+/// Length of an entry for a name this long
+pub(super) fn rec_len(name_len: usize) -> usize {
+    (8 + name_len + 3) & !3
+}
+
+/// A directory entry, at `offset` of its block
+#[derive(Debug, Clone)]
+pub(super) struct RawEntry {
+    pub offset: usize,
+    pub inode: u32,
+    pub rec_len: usize,
+    pub name: Vec<u8>,
+    pub file_type: u8,
+}
+
+fn has_tail(block: &[u8]) -> bool {
+    let tail = &block[block.len() - TAIL_SIZE..];
+    le_u32(tail, 0) == 0
+        && usize::from(le_u16(tail, 4)) == TAIL_SIZE
+        && tail[6] == 0
+        && tail[7] == TAIL_FILE_TYPE
+}
+
+/// Write an entry header and name at `offset`
+fn put_entry(
+    block: &mut [u8],
+    offset: usize,
+    inode: u32,
+    rec_len: usize,
+    name: &[u8],
+    file_type: u8,
+) {
+    put_u32(block, offset, inode);
+    put_u16(block, offset + 4, rec_len as u16);
+    block[offset + 6] = name.len() as u8;
+    block[offset + 7] = file_type;
+    block[offset + 8..offset + 8 + name.len()].copy_from_slice(name);
+}
+
+/// A directory block of no entries, with its checksum tail if `csum`
+pub(super) fn empty_block(size: usize, csum: bool) -> Vec<u8> {
+    let mut block = vec![0u8; size];
+    let limit = if csum { size - TAIL_SIZE } else { size };
+    put_entry(&mut block, 0, 0, limit, b"", 0);
+    if csum {
+        put_entry(&mut block, limit, 0, TAIL_SIZE, b"", TAIL_FILE_TYPE);
+    }
+    block
+}
+
+impl Ext4 {
+    fn filetype(&self) -> bool {
+        self.sb.incompat() & INCOMPAT_FILETYPE != 0
+    }
+
+    /// Entries of a directory block, deleted ones included
+    pub(super) fn parse_entries(&self, block: &[u8]) -> Result<Vec<RawEntry>> {
+        let limit = if self.sb.metadata_csum() && has_tail(block) {
+            block.len() - TAIL_SIZE
+        } else {
+            block.len()
+        };
+        let mut entries = Vec::new();
+        let mut offset = 0;
+        while offset < limit {
+            if offset + 8 > limit {
+                return Err(Error::InvalidFormat("corrupt directory block".to_string()));
+            }
+            let rec_len = usize::from(le_u16(block, offset + 4));
+            let (name_len, file_type) = if self.filetype() {
+                (usize::from(block[offset + 6]), block[offset + 7])
+            } else {
+                (usize::from(le_u16(block, offset + 6)), 0)
+            };
+            if rec_len < 8 || rec_len % 4 != 0 || offset + rec_len > limit || 8 + name_len > rec_len
+            {
+                return Err(Error::InvalidFormat("corrupt directory block".to_string()));
+            }
+            entries.push(RawEntry {
+                offset,
+                inode: le_u32(block, offset),
+                rec_len,
+                name: block[offset + 8..offset + 8 + name_len].to_vec(),
+                file_type,
+            });
+            offset += rec_len;
+        }
+        Ok(entries)
+    }
+
+    /// Blocks of directory `dir`, in order
+    pub(super) fn dir_blocks(&self, tx: &Tx, dir: &Inode) -> Result<Vec<Run>> {
+        let (runs, _) = self.map(tx, dir)?;
+        Ok(runs.into_iter().filter(|run| !run.uninit).collect())
+    }
+
+    /// Live entries of directory `dir`
+    pub(super) fn entries(&self, tx: &Tx, dir: &Inode) -> Result<Vec<RawEntry>> {
+        let mut entries = Vec::new();
+        for run in self.dir_blocks(tx, dir)? {
+            for block in run.physical..run.physical + u64::from(run.len) {
+                let data = self.read_block(tx, block)?;
+                entries.extend(
+                    self.parse_entries(&data)?
+                        .into_iter()
+                        .filter(|entry| entry.inode != 0),
+                );
+            }
+        }
+        Ok(entries)
+    }
+
+    pub(super) fn lookup(&self, tx: &Tx, dir: &Inode, name: &[u8]) -> Result<Option<RawEntry>> {
+        Ok(self
+            .entries(tx, dir)?
+            .into_iter()
+            .find(|entry| entry.name == name))
+    }
+
+    /// Resolve `path` from the root, following symbolic links on the way,
+    /// and the last component too if `follow`
+    pub(super) fn resolve(&self, tx: &Tx, path: &str, follow: bool) -> Result<Inode> {
+        let mut pending: Vec<Vec<u8>> = components(path).rev().collect();
+        let mut current = self.read_inode(tx, ROOT_INO)?;
+        let mut symlinks = 0;
+        while let Some(name) = pending.pop() {
+            if !current.is_dir() {
+                return Err(Error::NotFound(format!("{}: not a directory", path)));
+            }
+            let entry = self
+                .lookup(tx, &current, &name)?
+                .ok_or_else(|| Error::NotFound(path.to_string()))?;
+            let inode = self.read_inode(tx, entry.inode)?;
+            if inode.is_symlink() && (follow || !pending.is_empty()) {
+                symlinks += 1;
+                if symlinks > MAX_SYMLINKS {
+                    return Err(Error::InvalidFormat(format!(
+                        "{}: too many levels of symbolic links",
+                        path
+                    )));
+                }
+                let target = self.link_target(tx, &inode)?;
+                if target.starts_with(b"/") {
+                    current = self.read_inode(tx, ROOT_INO)?;
+                }
+                let target = String::from_utf8_lossy(&target).into_owned();
+                pending.extend(components(&target).rev());
+                continue;
+            }
+            current = inode;
+        }
+        Ok(current)
+    }
+
+    /// The directory `path` is in, and its last component
+    pub(super) fn resolve_parent<'p>(&self, tx: &Tx, path: &'p str) -> Result<(Inode, &'p str)> {
+        let path = path.trim_end_matches('/');
+        let (parent, name) = path.rsplit_once('/').unwrap_or(("", path));
+        if name.is_empty() || name == "." || name == ".." || name.len() > MAX_NAME_LEN {
+            return Err(Error::InputValidation(format!(
+                "Invalid file name: '{}'",
+                path
+            )));
+        }
+        let parent = self.resolve(tx, parent, true)?;
+        if !parent.is_dir() {
+            return Err(Error::NotFound(format!("{}: not a directory", path)));
+        }
+        Ok((parent, name))
+    }
+
+    pub(super) fn link_target(&self, tx: &Tx, inode: &Inode) -> Result<Vec<u8>> {
+        let size = inode.size() as usize;
+        if inode.is_fast_symlink() {
+            return Ok(inode.i_block()[..size].to_vec());
+        }
+        let data = self.read_data(tx, inode)?;
+        Ok(data[..size.min(data.len())].to_vec())
+    }
+
+    /// First block of the new directory `dir`: "." and ".." only
+    pub(super) fn new_dir_block(&self, dir: &Inode, parent: u32) -> Vec<u8> {
+        let size = self.sb.block_size as usize;
+        let csum = self.sb.metadata_csum();
+        let file_type = if self.filetype() { FT_DIR } else { 0 };
+        let mut block = empty_block(size, csum);
+        let limit = if csum { size - TAIL_SIZE } else { size };
+        put_entry(&mut block, 0, dir.ino, rec_len(1), b".", file_type);
+        put_entry(
+            &mut block,
+            rec_len(1),
+            parent,
+            limit - rec_len(1),
+            b"..",
+            file_type,
+        );
+        self.update_tail(dir, &mut block);
+        block
+    }
+
+    /// Add `name` for inode `ino` to directory `dir`
+    ///
+    /// `dir` may change, growing or losing its index; the caller writes it.
+    pub(super) fn add_entry(
+        &mut self,
+        tx: &mut Tx,
+        dir: &mut Inode,
+        name: &[u8],
+        ino: u32,
+        file_type: u8,
+    ) -> Result<()> {
+        let file_type = if self.filetype() { file_type } else { 0 };
+        let runs = self.dir_blocks(tx, dir)?;
+        if dir.flags() & INDEX_FL != 0 {
+            if let Some(leaf) = self.htree_leaf(tx, dir, &runs, name)? {
+                if self.insert_entry(tx, dir, leaf, name, ino, file_type)? {
+                    return Ok(());
+                }
+            }
+            log::debug!("ext4: dropping the htree index of inode {}", dir.ino);
+            self.drop_index(tx, dir, &runs)?;
+        }
+
+        for run in &runs {
+            for block in run.physical..run.physical + u64::from(run.len) {
+                if self.insert_entry(tx, dir, block, name, ino, file_type)? {
+                    return Ok(());
+                }
+            }
+        }
+
+        // No room: one more block
+        let block_size = self.sb.block_size;
+        let logical = dir.size().div_ceil(block_size) as u32;
+        let goal = runs.last().map_or(self.inode_goal(dir.ino), |run| {
+            run.physical + u64::from(run.len)
+        });
+        let (block, _) = self.alloc_blocks(tx, goal, 1)?[0];
+        tx.blocks.insert(
+            block,
+            empty_block(block_size as usize, self.sb.metadata_csum()),
+        );
+        if !self.insert_entry(tx, dir, block, name, ino, file_type)? {
+            return Err(Error::InvalidFormat(
+                "directory block too small".to_string(),
+            ));
+        }
+        let (mut runs, _) = self.map(tx, dir)?;
+        super::inode::push_run(
+            &mut runs,
+            Run {
+                logical,
+                physical: block,
+                len: 1,
+                uninit: false,
+            },
+        );
+        self.set_runs(tx, dir, &runs)?;
+        dir.set_size(u64::from(logical + 1) * block_size);
+        Ok(())
+    }
+
+    /// Put an entry in `block` if it has room
+    fn insert_entry(
+        &self,
+        tx: &mut Tx,
+        dir: &Inode,
+        block: u64,
+        name: &[u8],
+        ino: u32,
+        file_type: u8,
+    ) -> Result<bool> {
+        let csum = self.sb.metadata_csum();
+        let data = self.read_block(tx, block)?;
+        if csum && !has_tail(&data) {
+            return Ok(false);
+        }
+        let needed = rec_len(name.len());
+        for entry in self.parse_entries(&data)? {
+            let used = if entry.inode == 0 {
+                0
+            } else {
+                rec_len(entry.name.len())
+            };
+            if entry.rec_len < used + needed {
+                continue;
+            }
+            let data = self.block_mut(tx, block)?;
+            if used == 0 {
+                data[entry.offset..entry.offset + entry.rec_len].fill(0);
+                put_entry(data, entry.offset, ino, entry.rec_len, name, file_type);
+            } else {
+                put_u16(data, entry.offset + 4, used as u16);
+                let offset = entry.offset + used;
+                data[offset..offset + entry.rec_len - used].fill(0);
+                put_entry(data, offset, ino, entry.rec_len - used, name, file_type);
+            }
+            self.update_tail(dir, data);
+            return Ok(true);
+        }
+        Ok(false)
+    }
+
+    /// Remove `name` from directory `dir`, returning its inode
+    pub(super) fn remove_entry(&self, tx: &mut Tx, dir: &Inode, name: &[u8]) -> Result<u32> {
+        for run in self.dir_blocks(tx, dir)? {
+            for block in run.physical..run.physical + u64::from(run.len) {
+                let data = self.read_block(tx, block)?;
+                let entries = self.parse_entries(&data)?;
+                let Some(i) = entries
+                    .iter()
+                    .position(|entry| entry.inode != 0 && entry.name == name)
+                else {
+                    continue;
+                };
+                let entry = &entries[i];
+                let data = self.block_mut(tx, block)?;
+                // Merge into the entry before it, or mark deleted if first
+                match i.checked_sub(1).map(|prev| &entries[prev]) {
+                    Some(prev) => {
+                        put_u16(data, prev.offset + 4, (prev.rec_len + entry.rec_len) as u16)
+                    }
+                    None => put_u32(data, entry.offset, 0),
+                }
+                self.update_tail(dir, data);
+                return Ok(entry.inode);
+            }
+        }
+        Err(Error::NotFound(String::from_utf8_lossy(name).into_owned()))
+    }
+
+    fn update_tail(&self, dir: &Inode, block: &mut [u8]) {
+        if self.sb.metadata_csum() && has_tail(block) {
+            let end = block.len() - 4;
+            let csum = crc32c(dir.csum_seed(&self.sb), &block[..block.len() - TAIL_SIZE]);
+            put_u32(block, end, csum);
+        }
+    }
+
+    /// Leaf block an entry for `name` belongs in, as the kernel would pick
+    /// it; `None` if the index uses a hash this module doesn't know
+    fn htree_leaf(&self, tx: &Tx, dir: &Inode, runs: &[Run], name: &[u8]) -> Result<Option<u64>> {
+        let corrupt = || Error::InvalidFormat(format!("inode {}: corrupt htree", dir.ino));
+        let root = self.read_block(tx, physical(runs, 0).ok_or_else(corrupt)?)?;
+        let version = self.sb.hash_version(root[0x1c]);
+        let info_len = usize::from(root[0x1d]);
+        let levels = root[0x1e];
+        let Some(hash) = dirhash(name, version, self.sb.hash_seed()) else {
+            return Ok(None);
+        };
+
+        let mut node = root;
+        let mut entries_at = 0x18 + info_len;
+        for level in 0..=levels {
+            let count = usize::from(le_u16(&node, entries_at + 2));
+            if count == 0 || entries_at + 8 * count > node.len() {
+                return Err(corrupt());
+            }
+            // The last entry whose hash is not above ours; the first
+            // entry has no hash and covers everything below the second
+            let mut child = le_u32(&node, entries_at + 4);
+            for i in 1..count {
+                if le_u32(&node, entries_at + 8 * i) > hash {
+                    break;
+                }
+                child = le_u32(&node, entries_at + 8 * i + 4);
+            }
+            let block = physical(runs, child & 0x0fff_ffff).ok_or_else(corrupt)?;
+            if level == levels {
+                return Ok(Some(block));
+            }
+            node = self.read_block(tx, block)?;
+            entries_at = 8;
+        }
+        unreachable!()
+    }
+
+    /// Turn an indexed directory into a linear one: index blocks become
+    /// blocks of no entries
+    fn drop_index(&self, tx: &mut Tx, dir: &mut Inode, runs: &[Run]) -> Result<()> {
+        let csum = self.sb.metadata_csum();
+        let size = self.sb.block_size as usize;
+        let limit = if csum { size - TAIL_SIZE } else { size };
+        for run in runs {
+            for i in 0..run.len {
+                let block = run.physical + u64::from(i);
+                let data = self.read_block(tx, block)?;
+                if run.logical + i == 0 {
+                    // "." and "..", with ".." no longer spanning the index
+                    let data = self.block_mut(tx, block)?;
+                    let dot_len = usize::from(le_u16(data, 4));
+                    put_u16(data, dot_len + 4, (limit - dot_len) as u16);
+                    let end = dot_len + rec_len(usize::from(data[dot_len + 6]));
+                    data[end..].fill(0);
+                    if csum {
+                        put_entry(data, limit, 0, TAIL_SIZE, b"", TAIL_FILE_TYPE);
+                    }
+                    self.update_tail(dir, data);
+                } else if le_u32(&data, 0) == 0 && usize::from(le_u16(&data, 4)) == size {
+                    let mut empty = empty_block(size, csum);
+                    self.update_tail(dir, &mut empty);
+                    tx.blocks.insert(block, empty);
+                }
+            }
+        }
+        dir.set_flags(dir.flags() & !INDEX_FL);
+        Ok(())
+    }
+}
+
+/// Disk block of logical block `logical` of a directory
+pub(super) fn physical(runs: &[Run], logical: u32) -> Option<u64> {
+    runs.iter()
+        .find(|run| run.logical <= logical && logical < run.logical + run.len)
+        .map(|run| run.physical + u64::from(logical - run.logical))
+}
+
+fn components(path: &str) -> impl DoubleEndedIterator<Item = Vec<u8>> + '_ {
+    path.split('/')
+        .filter(|name| !name.is_empty() && *name != ".")
+        .map(|name| name.as_bytes().to_vec())
+}
+
+/// Hash versions of `s_def_hash_version`; +3 for the unsigned variants
+const DX_HASH_LEGACY: u8 = 0;
+const DX_HASH_HALF_MD4: u8 = 1;
+const DX_HASH_TEA: u8 = 2;
+const DX_HASH_UNSIGNED: u8 = 3;
+
+/// htree hash of `name`, as ext4's `ext4fs_dirhash`
+pub(super) fn dirhash(name: &[u8], version: u8, seed: [u32; 4]) -> Option<u32> {
+    let signed = version < DX_HASH_UNSIGNED;
+    let mut buf = if seed.iter().any(|&word| word != 0) {
+        seed
+    } else {
+        [0x6745_2301, 0xefcd_ab89, 0x98ba_dcfe, 0x1032_5476]
+    };
+    let hash = match version % DX_HASH_UNSIGNED {
+        _ if version > DX_HASH_TEA + DX_HASH_UNSIGNED => return None,
+        DX_HASH_LEGACY => legacy_hash(name, signed),
+        DX_HASH_HALF_MD4 => {
+            for chunk in remainders(name, 32) {
+                half_md4_transform(&mut buf, &str2hashbuf::<8>(chunk, signed));
+            }
+            buf[1]
+        }
+        _ => {
+            for chunk in remainders(name, 16) {
+                tea_transform(&mut buf, &str2hashbuf::<4>(chunk, signed));
+            }
+            buf[0]
+        }
+    };
+    let hash = hash & !1;
+    // The largest hash marks the end of directory reads
+    Some(if hash == 0x7fff_ffff << 1 {
+        (0x7fff_ffff - 1) << 1
+    } else {
+        hash
+    })
+}
+
+/// What is left of `name` at each step of `size` bytes, as the C loop
+/// decrementing `len` sees it
+fn remainders(name: &[u8], size: usize) -> impl Iterator<Item = &[u8]> {
+    (0..name.len()).step_by(size).map(|start| &name[start..])
+}
+
+fn char_value(byte: u8, signed: bool) -> u32 {
+    if signed {
+        byte as i8 as i32 as u32
+    } else {
+        u32::from(byte)
+    }
+}
+
+fn str2hashbuf<const N: usize>(msg: &[u8], signed: bool) -> [u32; N] {
+    let len = msg.len() as u32;
+    let mut pad = len | (len << 8);
+    pad |= pad << 16;
+    let mut out = [pad; N];
+    let mut val = pad;
+    let msg = &msg[..msg.len().min(N * 4)];
+    let mut word = 0;
+    for (i, &byte) in msg.iter().enumerate() {
+        val = char_value(byte, signed).wrapping_add(val << 8);
+        if i % 4 == 3 {
+            out[word] = val;
+            word += 1;
+            val = pad;
+        }
+    }
+    if word < N {
+        out[word] = val;
+    }
+    out
+}
+
+fn half_md4_transform(buf: &mut [u32; 4], input: &[u32; 8]) {
+    const K2: u32 = 0x5a82_7999;
+    const K3: u32 = 0x6ed9_eba1;
+    let f = |x: u32, y: u32, z: u32| z ^ (x & (y ^ z));
+    let g = |x: u32, y: u32, z: u32| (x & y).wrapping_add((x ^ y) & z);
+    let h = |x: u32, y: u32, z: u32| x ^ y ^ z;
+    let [mut a, mut b, mut c, mut d] = *buf;
+    macro_rules! round {
+        ($f:expr, $a:ident, $b:ident, $c:ident, $d:ident, $x:expr, $s:expr) => {
+            $a = $a
+                .wrapping_add($f($b, $c, $d))
+                .wrapping_add($x)
+                .rotate_left($s);
+        };
+    }
+    round!(f, a, b, c, d, input[0], 3);
+    round!(f, d, a, b, c, input[1], 7);
+    round!(f, c, d, a, b, input[2], 11);
+    round!(f, b, c, d, a, input[3], 19);
+    round!(f, a, b, c, d, input[4], 3);
+    round!(f, d, a, b, c, input[5], 7);
+    round!(f, c, d, a, b, input[6], 11);
+    round!(f, b, c, d, a, input[7], 19);
+
+    round!(g, a, b, c, d, input[1].wrapping_add(K2), 3);
+    round!(g, d, a, b, c, input[3].wrapping_add(K2), 5);
+    round!(g, c, d, a, b, input[5].wrapping_add(K2), 9);
+    round!(g, b, c, d, a, input[7].wrapping_add(K2), 13);
+    round!(g, a, b, c, d, input[0].wrapping_add(K2), 3);
+    round!(g, d, a, b, c, input[2].wrapping_add(K2), 5);
+    round!(g, c, d, a, b, input[4].wrapping_add(K2), 9);
+    round!(g, b, c, d, a, input[6].wrapping_add(K2), 13);
+
+    round!(h, a, b, c, d, input[3].wrapping_add(K3), 3);
+    round!(h, d, a, b, c, input[7].wrapping_add(K3), 9);
+    round!(h, c, d, a, b, input[2].wrapping_add(K3), 11);
+    round!(h, b, c, d, a, input[6].wrapping_add(K3), 15);
+    round!(h, a, b, c, d, input[1].wrapping_add(K3), 3);
+    round!(h, d, a, b, c, input[5].wrapping_add(K3), 9);
+    round!(h, c, d, a, b, input[0].wrapping_add(K3), 11);
+    round!(h, b, c, d, a, input[4].wrapping_add(K3), 15);
+
+    buf[0] = buf[0].wrapping_add(a);
+    buf[1] = buf[1].wrapping_add(b);
+    buf[2] = buf[2].wrapping_add(c);
+    buf[3] = buf[3].wrapping_add(d);
+}
+
+fn tea_transform(buf: &mut [u32; 4], input: &[u32; 4]) {
+    const DELTA: u32 = 0x9e37_79b9;
+    let (mut b0, mut b1) = (buf[0], buf[1]);
+    let [a, b, c, d] = *input;
+    let mut sum = 0u32;
+    for _ in 0..16 {
+        sum = sum.wrapping_add(DELTA);
+        b0 = b0.wrapping_add(
+            (b1 << 4).wrapping_add(a) ^ b1.wrapping_add(sum) ^ (b1 >> 5).wrapping_add(b),
+        );
+        b1 = b1.wrapping_add(
+            (b0 << 4).wrapping_add(c) ^ b0.wrapping_add(sum) ^ (b0 >> 5).wrapping_add(d),
+        );
+    }
+    buf[0] = buf[0].wrapping_add(b0);
+    buf[1] = buf[1].wrapping_add(b1);
+}
+
+fn legacy_hash(name: &[u8], signed: bool) -> u32 {
+    let (mut hash0, mut hash1) = (0x12a3_fe2d_u32, 0x37ab_e8f9_u32);
+    for &byte in name {
+        let mut hash = hash1.wrapping_add(hash0 ^ char_value(byte, signed).wrapping_mul(7_152_373));
+        if hash & 0x8000_0000 != 0 {
+            hash = hash.wrapping_sub(0x7fff_ffff);
+        }
+        hash1 = hash0;
+        hash0 = hash;
+    }
+    hash0 << 1
+}
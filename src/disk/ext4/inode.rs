@@ -0,0 +1,557 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Inodes and the extent trees mapping their blocks
+
+use super::layout::{crc32c, le_u16, le_u32, put_u16, put_u32, Superblock, RO_COMPAT_HUGE_FILE};
+use super::{Ext4, Tx};
+use crate::core::{Error, Result};
+
+pub(super) const S_IFMT: u16 = 0o170000;
+pub(super) const S_IFREG: u16 = 0o100000;
+pub(super) const S_IFDIR: u16 = 0o040000;
+pub(super) const S_IFLNK: u16 = 0o120000;
+
+/// Inode flags
+pub(super) const INDEX_FL: u32 = 0x1000;
+const HUGE_FILE_FL: u32 = 0x40000;
+pub(super) const EXTENTS_FL: u32 = 0x80000;
+const INLINE_DATA_FL: u32 = 0x1000_0000;
+
+pub(super) const EXTENT_MAGIC: u16 = 0xf30a;
+/// Extents in the inode itself
+const INODE_EXTENTS: u16 = 4;
+/// Longest extent, and longest uninitialized one
+const MAX_EXTENT_LEN: u32 = 32768;
+const MAX_UNINIT_EXTENT_LEN: u32 = 32767;
+/// ext4 refuses deeper trees
+const MAX_EXTENT_DEPTH: u16 = 5;
+
+/// Block pointers of `i_block`: direct, then single, double and triple
+/// indirect, for files without extents
+const DIRECT_BLOCKS: usize = 12;
+
+/// Offsets of the extra fields, valid when `i_extra_isize` covers them
+const EXTRA_START: usize = 128;
+const CTIME_EXTRA: usize = 0x84;
+const CRTIME: usize = 0x90;
+
+/// Blocks `logical..logical + len` of a file at `physical` on disk
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Run {
+    pub logical: u32,
+    pub physical: u64,
+    pub len: u32,
+    /// Allocated but reading as zeros
+    pub uninit: bool,
+}
+
+/// Append a block run, merging it with the last one where it continues it
+pub(super) fn push_run(runs: &mut Vec<Run>, run: Run) {
+    if let Some(last) = runs.last_mut() {
+        if last.uninit == run.uninit
+            && last.logical + last.len == run.logical
+            && last.physical + u64::from(last.len) == run.physical
+        {
+            last.len += run.len;
+            return;
+        }
+    }
+    runs.push(run);
+}
+
+/// An on-disk inode
+#[derive(Clone)]
+pub(super) struct Inode {
+    pub ino: u32,
+    pub raw: Vec<u8>,
+}
+
+impl Inode {
+    /// A new inode with no blocks, owned by root
+    pub fn new(ino: u32, mode: u16, links: u16, now: u32, sb: &Superblock) -> Self {
+        let mut inode = Self {
+            ino,
+            raw: vec![0; sb.inode_size],
+        };
+        put_u16(&mut inode.raw, 0x0, mode);
+        put_u16(&mut inode.raw, 0x1a, links);
+        put_u32(&mut inode.raw, 0x20, EXTENTS_FL);
+        // Inode numbers are reused; the generation tells NFS clients apart
+        let generation = ino ^ now.rotate_left(16) ^ std::process::id();
+        put_u32(&mut inode.raw, 0x64, generation);
+        let extra = sb.extra_isize();
+        if extra > 0 {
+            put_u16(&mut inode.raw, 0x80, extra);
+        }
+        if inode.has_extra(CRTIME + 4) {
+            put_u32(&mut inode.raw, CRTIME, now);
+        }
+        inode.set_times(now);
+        inode.raw[0x28..0x64].copy_from_slice(&extent_root(&[], 0));
+        inode
+    }
+
+    pub fn mode(&self) -> u16 {
+        le_u16(&self.raw, 0x0)
+    }
+
+    pub fn set_mode(&mut self, mode: u16) {
+        put_u16(&mut self.raw, 0x0, mode);
+    }
+
+    pub fn file_type(&self) -> u16 {
+        self.mode() & S_IFMT
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.file_type() == S_IFDIR
+    }
+
+    pub fn is_reg(&self) -> bool {
+        self.file_type() == S_IFREG
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.file_type() == S_IFLNK
+    }
+
+    pub fn uid(&self) -> u32 {
+        u32::from(le_u16(&self.raw, 0x2)) | u32::from(le_u16(&self.raw, 0x78)) << 16
+    }
+
+    pub fn gid(&self) -> u32 {
+        u32::from(le_u16(&self.raw, 0x18)) | u32::from(le_u16(&self.raw, 0x7a)) << 16
+    }
+
+    pub fn size(&self) -> u64 {
+        u64::from(le_u32(&self.raw, 0x4)) | u64::from(le_u32(&self.raw, 0x6c)) << 32
+    }
+
+    pub fn set_size(&mut self, size: u64) {
+        put_u32(&mut self.raw, 0x4, size as u32);
+        put_u32(&mut self.raw, 0x6c, (size >> 32) as u32);
+    }
+
+    pub fn links(&self) -> u16 {
+        le_u16(&self.raw, 0x1a)
+    }
+
+    pub fn set_links(&mut self, links: u16) {
+        put_u16(&mut self.raw, 0x1a, links);
+    }
+
+    pub fn flags(&self) -> u32 {
+        le_u32(&self.raw, 0x20)
+    }
+
+    pub fn set_flags(&mut self, flags: u32) {
+        put_u32(&mut self.raw, 0x20, flags);
+    }
+
+    pub fn mtime(&self) -> u32 {
+        le_u32(&self.raw, 0x10)
+    }
+
+    /// Deletion time, or the next inode on the orphan list
+    pub fn dtime(&self) -> u32 {
+        le_u32(&self.raw, 0x14)
+    }
+
+    pub fn generation(&self) -> u32 {
+        le_u32(&self.raw, 0x64)
+    }
+
+    /// Extended attribute block, 0 if none
+    pub fn xattr_block(&self) -> u64 {
+        u64::from(le_u32(&self.raw, 0x68)) | u64::from(le_u16(&self.raw, 0x76)) << 32
+    }
+
+    pub fn i_block(&self) -> &[u8] {
+        &self.raw[0x28..0x64]
+    }
+
+    fn has_extra(&self, end: usize) -> bool {
+        self.raw.len() > EXTRA_START && EXTRA_START + usize::from(le_u16(&self.raw, 0x80)) >= end
+    }
+
+    /// Set the access, change and modification times
+    pub fn set_times(&mut self, now: u32) {
+        for offset in [0x8, 0xc, 0x10] {
+            put_u32(&mut self.raw, offset, now);
+        }
+        self.clear_extra_times(3);
+    }
+
+    /// Set the change and modification times, as a write does
+    pub fn set_modified(&mut self, now: u32) {
+        for offset in [0xc, 0x10] {
+            put_u32(&mut self.raw, offset, now);
+        }
+        self.clear_extra_times(2);
+    }
+
+    pub fn set_ctime(&mut self, now: u32) {
+        put_u32(&mut self.raw, 0xc, now);
+        self.clear_extra_times(1);
+    }
+
+    /// Zero the nanoseconds of ctime, mtime and atime, in that order
+    fn clear_extra_times(&mut self, count: usize) {
+        for i in 0..count {
+            let offset = CTIME_EXTRA + 4 * i;
+            if self.has_extra(offset + 4) {
+                put_u32(&mut self.raw, offset, 0);
+            }
+        }
+    }
+
+    pub fn set_dtime(&mut self, now: u32) {
+        put_u32(&mut self.raw, 0x14, now);
+    }
+
+    /// Set the blocks the inode holds, data and metadata
+    pub fn set_blocks(&mut self, blocks: u64, sb: &Superblock) -> Result<()> {
+        let sectors = blocks * (sb.block_size / 512);
+        let limit = if sb.ro_compat() & RO_COMPAT_HUGE_FILE != 0 {
+            1 << 48
+        } else {
+            1 << 32
+        };
+        if sectors >= limit {
+            return Err(Error::Unsupported(format!(
+                "inode {}: {} blocks",
+                self.ino, blocks
+            )));
+        }
+        self.set_flags(self.flags() & !HUGE_FILE_FL);
+        put_u32(&mut self.raw, 0x1c, sectors as u32);
+        put_u16(&mut self.raw, 0x74, (sectors >> 32) as u16);
+        Ok(())
+    }
+
+    /// Fast symlinks keep their target in `i_block`
+    pub fn is_fast_symlink(&self) -> bool {
+        self.is_symlink() && self.size() < 60 && self.flags() & INLINE_DATA_FL == 0
+    }
+
+    /// Seed of the checksums of the inode and the blocks it owns
+    pub fn csum_seed(&self, sb: &Superblock) -> u32 {
+        let crc = crc32c(sb.csum_seed, &self.ino.to_le_bytes());
+        crc32c(crc, &self.raw[0x64..0x68])
+    }
+
+    pub fn update_checksum(&mut self, sb: &Superblock) {
+        if !sb.metadata_csum() {
+            return;
+        }
+        let seed = self.csum_seed(sb);
+        let has_hi = self.has_extra(0x84);
+        put_u16(&mut self.raw, 0x7c, 0);
+        if has_hi {
+            put_u16(&mut self.raw, 0x82, 0);
+        }
+        let csum = crc32c(seed, &self.raw);
+        put_u16(&mut self.raw, 0x7c, csum as u16);
+        if has_hi {
+            put_u16(&mut self.raw, 0x82, (csum >> 16) as u16);
+        }
+    }
+}
+
+/// Extent tree header and entries: leaf extents when `depth` is 0, index
+/// entries `(first logical block, child block)` otherwise
+fn extent_node(size: usize, max: u16, depth: u16, entries: &[(u32, u64, u16)]) -> Vec<u8> {
+    let mut node = vec![0u8; size];
+    put_u16(&mut node, 0x0, EXTENT_MAGIC);
+    put_u16(&mut node, 0x2, entries.len() as u16);
+    put_u16(&mut node, 0x4, max);
+    put_u16(&mut node, 0x6, depth);
+    for (i, &(logical, physical, len)) in entries.iter().enumerate() {
+        let entry = &mut node[12 + 12 * i..24 + 12 * i];
+        put_u32(entry, 0, logical);
+        if depth == 0 {
+            put_u16(entry, 4, len);
+            put_u16(entry, 6, (physical >> 32) as u16);
+            put_u32(entry, 8, physical as u32);
+        } else {
+            put_u32(entry, 4, physical as u32);
+            put_u16(entry, 8, (physical >> 32) as u16);
+        }
+    }
+    node
+}
+
+fn extent_root(entries: &[(u32, u64, u16)], depth: u16) -> Vec<u8> {
+    extent_node(60, INODE_EXTENTS, depth, entries)
+}
+
+impl Ext4 {
+    /// Block and byte offset of inode `ino` in its inode table
+    fn inode_location(&self, ino: u32) -> Result<(u64, usize)> {
+        if ino == 0 || ino > self.sb.inodes_count {
+            return Err(Error::InvalidFormat(format!(
+                "invalid inode number {}",
+                ino
+            )));
+        }
+        let group = (ino - 1) / self.sb.inodes_per_group;
+        let index = u64::from((ino - 1) % self.sb.inodes_per_group);
+        let offset = index * self.sb.inode_size as u64;
+        Ok((
+            self.groups.inode_table(group) + offset / self.sb.block_size,
+            (offset % self.sb.block_size) as usize,
+        ))
+    }
+
+    pub(super) fn read_inode(&self, tx: &Tx, ino: u32) -> Result<Inode> {
+        let (block, offset) = self.inode_location(ino)?;
+        let data = self.read_block(tx, block)?;
+        Ok(Inode {
+            ino,
+            raw: data[offset..offset + self.sb.inode_size].to_vec(),
+        })
+    }
+
+    pub(super) fn write_inode(&self, tx: &mut Tx, inode: &mut Inode) -> Result<()> {
+        inode.update_checksum(&self.sb);
+        let (block, offset) = self.inode_location(inode.ino)?;
+        let data = self.block_mut(tx, block)?;
+        data[offset..offset + inode.raw.len()].copy_from_slice(&inode.raw);
+        Ok(())
+    }
+
+    /// Data blocks of `inode`, and the blocks of the tree mapping them
+    pub(super) fn map(&self, tx: &Tx, inode: &Inode) -> Result<(Vec<Run>, Vec<u64>)> {
+        let mut runs = Vec::new();
+        let mut tree = Vec::new();
+        if inode.flags() & INLINE_DATA_FL != 0 {
+            return Err(Error::Unsupported(format!(
+                "inode {} keeps its data inline",
+                inode.ino
+            )));
+        }
+        if inode.is_fast_symlink() {
+            return Ok((runs, tree));
+        }
+        if inode.flags() & EXTENTS_FL != 0 {
+            self.map_extents(tx, inode, inode.i_block(), None, &mut runs, &mut tree)?;
+        } else {
+            for (i, pointer) in inode.i_block().chunks(4).enumerate() {
+                let block = u64::from(le_u32(pointer, 0));
+                if block == 0 {
+                    continue;
+                }
+                if i < DIRECT_BLOCKS {
+                    self.check_block(inode, block)?;
+                    push_run(
+                        &mut runs,
+                        Run {
+                            logical: i as u32,
+                            physical: block,
+                            len: 1,
+                            uninit: false,
+                        },
+                    );
+                } else {
+                    let level = (i - DIRECT_BLOCKS + 1) as u32;
+                    let per_block = (self.sb.block_size / 4) as u32;
+                    // Logical blocks before those of this indirect tree
+                    let first = (1..level).fold(DIRECT_BLOCKS as u32, |first, l| {
+                        first.saturating_add(per_block.saturating_pow(l))
+                    });
+                    self.map_indirect(tx, inode, block, level, first, &mut runs, &mut tree)?;
+                }
+            }
+        }
+        Ok((runs, tree))
+    }
+
+    fn check_block(&self, inode: &Inode, block: u64) -> Result<()> {
+        if block <= self.sb.first_data_block || block >= self.sb.blocks_count {
+            return Err(Error::InvalidFormat(format!(
+                "inode {} maps invalid block {}",
+                inode.ino, block
+            )));
+        }
+        Ok(())
+    }
+
+    fn map_extents(
+        &self,
+        tx: &Tx,
+        inode: &Inode,
+        node: &[u8],
+        expected_depth: Option<u16>,
+        runs: &mut Vec<Run>,
+        tree: &mut Vec<u64>,
+    ) -> Result<()> {
+        let corrupt = || Error::InvalidFormat(format!("inode {}: corrupt extent tree", inode.ino));
+        let entries = usize::from(le_u16(node, 0x2));
+        let depth = le_u16(node, 0x6);
+        if le_u16(node, 0x0) != EXTENT_MAGIC
+            || 12 + 12 * entries > node.len()
+            || depth > MAX_EXTENT_DEPTH
+            || expected_depth.is_some_and(|expected| expected != depth)
+        {
+            return Err(corrupt());
+        }
+        for entry in node[12..12 + 12 * entries].chunks(12) {
+            let logical = le_u32(entry, 0);
+            if depth == 0 {
+                let len = u32::from(le_u16(entry, 4));
+                let (len, uninit) = if len > MAX_EXTENT_LEN {
+                    (len - MAX_EXTENT_LEN, true)
+                } else {
+                    (len, false)
+                };
+                let physical = u64::from(le_u16(entry, 6)) << 32 | u64::from(le_u32(entry, 8));
+                if len == 0 {
+                    return Err(corrupt());
+                }
+                self.check_block(inode, physical)?;
+                self.check_block(inode, physical + u64::from(len) - 1)?;
+                push_run(
+                    runs,
+                    Run {
+                        logical,
+                        physical,
+                        len,
+                        uninit,
+                    },
+                );
+            } else {
+                let child = u64::from(le_u16(entry, 8)) << 32 | u64::from(le_u32(entry, 4));
+                self.check_block(inode, child)?;
+                tree.push(child);
+                let data = self.read_block(tx, child)?;
+                self.map_extents(tx, inode, &data, Some(depth - 1), runs, tree)?;
+            }
+        }
+        Ok(())
+    }
+
+    #[allow(clippy::too_many_arguments)]
+    fn map_indirect(
+        &self,
+        tx: &Tx,
+        inode: &Inode,
+        block: u64,
+        level: u32,
+        first: u32,
+        runs: &mut Vec<Run>,
+        tree: &mut Vec<u64>,
+    ) -> Result<()> {
+        self.check_block(inode, block)?;
+        tree.push(block);
+        let data = self.read_block(tx, block)?;
+        let per_block = (self.sb.block_size / 4) as u32;
+        let span = per_block.saturating_pow(level - 1);
+        for (i, pointer) in data.chunks(4).enumerate() {
+            let child = u64::from(le_u32(pointer, 0));
+            if child == 0 {
+                continue;
+            }
+            let logical = first.saturating_add((i as u32).saturating_mul(span));
+            if level == 1 {
+                self.check_block(inode, child)?;
+                push_run(
+                    runs,
+                    Run {
+                        logical,
+                        physical: child,
+                        len: 1,
+                        uninit: false,
+                    },
+                );
+            } else {
+                self.map_indirect(tx, inode, child, level - 1, logical, runs, tree)?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Point `inode` at `runs`, rebuilding its extent tree
+    ///
+    /// Blocks of the old tree are freed, the data blocks are left to the
+    /// caller. Sets the block count of the inode.
+    pub(super) fn set_runs(&mut self, tx: &mut Tx, inode: &mut Inode, runs: &[Run]) -> Result<()> {
+        let (_, old_tree) = self.map(tx, inode)?;
+
+        let mut extents = Vec::new();
+        let mut data_blocks = 0u64;
+        for run in runs {
+            let max = if run.uninit {
+                MAX_UNINIT_EXTENT_LEN
+            } else {
+                MAX_EXTENT_LEN
+            };
+            let mut done = 0;
+            while done < run.len {
+                let len = (run.len - done).min(max);
+                let stored = if run.uninit {
+                    len + MAX_EXTENT_LEN
+                } else {
+                    len
+                };
+                extents.push((
+                    run.logical + done,
+                    run.physical + u64::from(done),
+                    stored as u16,
+                ));
+                done += len;
+            }
+            data_blocks += u64::from(run.len);
+        }
+
+        let block_size = self.sb.block_size as usize;
+        let per_leaf = (block_size - 12) / 12;
+        let root = if extents.len() <= usize::from(INODE_EXTENTS) {
+            extent_root(&extents, 0)
+        } else {
+            let leaves = extents.chunks(per_leaf).count();
+            if leaves > usize::from(INODE_EXTENTS) {
+                return Err(Error::Unsupported(format!(
+                    "inode {}: {} extents need a deeper extent tree",
+                    inode.ino,
+                    extents.len()
+                )));
+            }
+            let goal = extents[0].1;
+            let blocks = self.alloc_blocks(tx, goal, leaves as u64)?;
+            let blocks: Vec<u64> = blocks
+                .iter()
+                .flat_map(|&(start, len)| start..start + len)
+                .collect();
+            let seed = inode.csum_seed(&self.sb);
+            let mut index = Vec::new();
+            for (chunk, &block) in extents.chunks(per_leaf).zip(&blocks) {
+                let mut leaf = extent_node(block_size, per_leaf as u16, 0, chunk);
+                if self.sb.metadata_csum() {
+                    let tail = 12 + 12 * per_leaf;
+                    let csum = crc32c(seed, &leaf[..tail]);
+                    put_u32(&mut leaf, tail, csum);
+                }
+                tx.blocks.insert(block, leaf);
+                index.push((chunk[0].0, block, 0));
+            }
+            extent_root(&index, 1)
+        };
+        let tree_blocks = root_tree_blocks(&root);
+
+        for block in old_tree {
+            self.free_blocks(tx, block, 1)?;
+        }
+        inode.raw[0x28..0x64].copy_from_slice(&root);
+        inode.set_flags(inode.flags() | EXTENTS_FL);
+        let xattr_blocks = u64::from(inode.xattr_block() != 0);
+        inode.set_blocks(data_blocks + tree_blocks + xattr_blocks, &self.sb)
+    }
+}
+
+/// Leaf blocks under an extent root built by `set_runs`
+fn root_tree_blocks(root: &[u8]) -> u64 {
+    if le_u16(root, 0x6) == 0 {
+        0
+    } else {
+        u64::from(le_u16(root, 0x2))
+    }
+}
@@ -0,0 +1,445 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Transactions in the ext4 journal (jbd2)
+//!
+//! Every change is one transaction, written the way the kernel commits
+//! one: descriptor blocks listing the metadata blocks, copies of those
+//! blocks and a commit block. Only once the transaction is on disk does
+//! the journal superblock point at it; the blocks are then written in
+//! place and the journal marked empty again. A crash at any point leaves
+//! either the old metadata or a journal that `e2fsck` or the next mount
+//! replays into the new one.
+
+use super::inode::Run;
+use super::layout::crc32c;
+use crate::core::{Error, Result};
+use std::collections::BTreeMap;
+
+const MAGIC: u32 = 0xc03b_3998;
+
+/// Block types
+const DESCRIPTOR_BLOCK: u32 = 1;
+const COMMIT_BLOCK: u32 = 2;
+const SUPERBLOCK_V1: u32 = 3;
+const SUPERBLOCK_V2: u32 = 4;
+
+/// Commit blocks carry a CRC-32 of the transaction (before csum v2)
+const COMPAT_CHECKSUM: u32 = 0x1;
+
+const INCOMPAT_REVOKE: u32 = 0x1;
+const INCOMPAT_64BIT: u32 = 0x2;
+const INCOMPAT_ASYNC_COMMIT: u32 = 0x4;
+const INCOMPAT_CSUM_V2: u32 = 0x8;
+const INCOMPAT_CSUM_V3: u32 = 0x10;
+const INCOMPAT_FAST_COMMIT: u32 = 0x20;
+/// Features transactions can be written for
+const INCOMPAT_WRITE: u32 =
+    INCOMPAT_REVOKE | INCOMPAT_64BIT | INCOMPAT_CSUM_V3 | INCOMPAT_FAST_COMMIT;
+
+/// Descriptor tag flags
+const FLAG_ESCAPE: u32 = 0x1;
+const FLAG_SAME_UUID: u32 = 0x2;
+const FLAG_LAST_TAG: u32 = 0x8;
+
+/// Blocks the kernel reserves for fast commits when the superblock says 0
+const DEFAULT_FAST_COMMIT_BLOCKS: u32 = 256;
+
+const UUID_SIZE: usize = 16;
+const HEADER_SIZE: usize = 12;
+
+/// Journal blocks of a transaction, and the disk blocks they go to
+pub(super) type Records = Vec<(u64, Vec<u8>)>;
+
+/// A copy of a filesystem block still in the journal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Logged {
+    /// Filesystem block it is a copy of
+    pub block: u64,
+    /// Transaction that logged it
+    pub sequence: u32,
+    /// Journal block holding the copy
+    pub index: u32,
+    /// The copy's first four bytes were the journal magic and read as zeros
+    pub escaped: bool,
+}
+
+fn be_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn put_be32(buf: &mut [u8], offset: usize, value: u32) {
+    buf[offset..offset + 4].copy_from_slice(&value.to_be_bytes());
+}
+
+fn header(buf: &mut [u8], block_type: u32, sequence: u32) {
+    put_be32(buf, 0, MAGIC);
+    put_be32(buf, 4, block_type);
+    put_be32(buf, 8, sequence);
+}
+
+/// The journal of a filesystem, empty between transactions
+pub(super) struct Journal {
+    /// Where the journal inode's blocks are on disk
+    runs: Vec<Run>,
+    /// Journal block 0, the superblock in its first kilobyte
+    superblock: Vec<u8>,
+    block_size: usize,
+}
+
+impl Journal {
+    /// Take over the journal whose inode maps `runs` and whose first block
+    /// is `superblock`
+    pub fn load(runs: Vec<Run>, superblock: Vec<u8>) -> Result<Self> {
+        let journal = Self {
+            runs,
+            block_size: superblock.len(),
+            superblock,
+        };
+        let sb = &journal.superblock;
+        let block_type = be_u32(sb, 4);
+        if be_u32(sb, 0) != MAGIC || !matches!(block_type, SUPERBLOCK_V1 | SUPERBLOCK_V2) {
+            return Err(Error::InvalidFormat(
+                "corrupt journal superblock".to_string(),
+            ));
+        }
+        if be_u32(sb, 0xc) as usize != journal.block_size {
+            return Err(Error::Unsupported(
+                "journal block size differs from the filesystem's".to_string(),
+            ));
+        }
+        let mapped: u64 = journal.runs.iter().map(|run| u64::from(run.len)).sum();
+        if u64::from(journal.max_len()) > mapped || journal.first() == 0 {
+            return Err(Error::InvalidFormat(
+                "corrupt journal superblock".to_string(),
+            ));
+        }
+        if be_u32(sb, 0x1c) != 0 {
+            return Err(Error::InvalidState(
+                "the journal needs recovery; run e2fsck first".to_string(),
+            ));
+        }
+        if journal.v2() {
+            let incompat = be_u32(sb, 0x28) & !INCOMPAT_WRITE;
+            if be_u32(sb, 0x24) & COMPAT_CHECKSUM != 0
+                || incompat & (INCOMPAT_ASYNC_COMMIT | INCOMPAT_CSUM_V2) != 0
+            {
+                return Err(Error::Unsupported(
+                    "journals with v1 or v2 checksums".to_string(),
+                ));
+            }
+            if incompat != 0 {
+                return Err(Error::Unsupported(format!(
+                    "journal features 0x{:x}",
+                    incompat
+                )));
+            }
+            if journal.csum_v3()
+                && crc32c(!0, &journal.checksummed_superblock()) != be_u32(sb, 0xfc)
+            {
+                return Err(Error::InvalidFormat(
+                    "journal superblock checksum mismatch".to_string(),
+                ));
+            }
+        }
+        Ok(journal)
+    }
+
+    fn v2(&self) -> bool {
+        be_u32(&self.superblock, 4) == SUPERBLOCK_V2
+    }
+
+    fn incompat(&self) -> u32 {
+        if self.v2() {
+            be_u32(&self.superblock, 0x28)
+        } else {
+            0
+        }
+    }
+
+    fn csum_v3(&self) -> bool {
+        self.incompat() & INCOMPAT_CSUM_V3 != 0
+    }
+
+    fn max_len(&self) -> u32 {
+        be_u32(&self.superblock, 0x10)
+    }
+
+    fn first(&self) -> u32 {
+        be_u32(&self.superblock, 0x14)
+    }
+
+    fn sequence(&self) -> u32 {
+        be_u32(&self.superblock, 0x18)
+    }
+
+    /// Last block transactions may use; fast commits go after it
+    fn last(&self) -> u32 {
+        if self.incompat() & INCOMPAT_FAST_COMMIT == 0 {
+            return self.max_len();
+        }
+        let fast_commit = match be_u32(&self.superblock, 0x54) {
+            0 => DEFAULT_FAST_COMMIT_BLOCKS,
+            blocks => blocks,
+        };
+        self.max_len().saturating_sub(fast_commit)
+    }
+
+    fn uuid(&self) -> &[u8] {
+        &self.superblock[0x30..0x30 + UUID_SIZE]
+    }
+
+    fn csum_seed(&self) -> u32 {
+        crc32c(!0, self.uuid())
+    }
+
+    /// Disk block of journal block `index`
+    pub fn physical(&self, index: u32) -> u64 {
+        let run = self
+            .runs
+            .iter()
+            .find(|run| run.logical <= index && index < run.logical + run.len)
+            .expect("journal blocks are mapped up to its length");
+        run.physical + u64::from(index - run.logical)
+    }
+
+    /// The superblock as its checksum covers it: the first kilobyte, with
+    /// the checksum field zeroed
+    fn checksummed_superblock(&self) -> Vec<u8> {
+        let mut sb = self.superblock[..1024].to_vec();
+        put_be32(&mut sb, 0xfc, 0);
+        sb
+    }
+
+    /// Journal block 0 pointing at a transaction at `start` with ID
+    /// `sequence`, or empty when `start` is 0
+    pub fn superblock(&self, start: u32, sequence: u32) -> Vec<u8> {
+        let mut block = self.superblock.clone();
+        put_be32(&mut block, 0x18, sequence);
+        put_be32(&mut block, 0x1c, start);
+        if self.csum_v3() {
+            put_be32(&mut block, 0xfc, 0);
+            let csum = crc32c(!0, &block[..1024]);
+            put_be32(&mut block, 0xfc, csum);
+        }
+        block
+    }
+
+    /// Journal records of a transaction writing `blocks`, and where they go
+    pub fn transaction(&self, blocks: &BTreeMap<u64, Vec<u8>>, now: u64) -> Result<Records> {
+        let sequence = self.sequence();
+        let csum = self.csum_v3();
+        let seed = self.csum_seed();
+        let wide = self.incompat() & INCOMPAT_64BIT != 0;
+        let tag_size = match (csum, wide) {
+            (true, _) => 16,
+            (false, true) => 12,
+            (false, false) => 8,
+        };
+        let end = self.block_size - if csum { 4 } else { 0 };
+
+        let mut records = Vec::new();
+        let mut pending = blocks.iter().peekable();
+        while pending.peek().is_some() {
+            let mut descriptor = vec![0u8; self.block_size];
+            header(&mut descriptor, DESCRIPTOR_BLOCK, sequence);
+            let mut copies = Vec::new();
+            let mut offset = HEADER_SIZE;
+            let mut last_tag = 0;
+            // Room for one more tag, and a UUID after the first
+            while let Some((&block, data)) =
+                pending.next_if(|_| offset + tag_size + UUID_SIZE <= end)
+            {
+                let mut copy = data.clone();
+                let mut flags = 0;
+                // A copy must not look like a journal block on replay
+                if be_u32(&copy, 0) == MAGIC {
+                    copy[..4].fill(0);
+                    flags |= FLAG_ESCAPE;
+                }
+                if !copies.is_empty() {
+                    flags |= FLAG_SAME_UUID;
+                }
+                let tag = &mut descriptor[offset..offset + tag_size];
+                put_be32(tag, 0, block as u32);
+                if csum {
+                    put_be32(tag, 4, flags);
+                    put_be32(tag, 8, (block >> 32) as u32);
+                    let crc = crc32c(seed, &sequence.to_be_bytes());
+                    put_be32(tag, 12, crc32c(crc, &copy));
+                } else {
+                    tag[6..8].copy_from_slice(&(flags as u16).to_be_bytes());
+                    if wide {
+                        put_be32(tag, 8, (block >> 32) as u32);
+                    } else if block >> 32 != 0 {
+                        return Err(Error::Unsupported(
+                            "blocks past 2^32 in a 32-bit journal".to_string(),
+                        ));
+                    }
+                }
+                last_tag = offset;
+                offset += tag_size;
+                if copies.is_empty() {
+                    descriptor[offset..offset + UUID_SIZE].copy_from_slice(self.uuid());
+                    offset += UUID_SIZE;
+                }
+                copies.push(copy);
+            }
+            if csum {
+                let flags = be_u32(&descriptor, last_tag + 4) | FLAG_LAST_TAG;
+                put_be32(&mut descriptor, last_tag + 4, flags);
+                let crc = crc32c(seed, &descriptor);
+                put_be32(&mut descriptor, end, crc);
+            } else {
+                let flags =
+                    u16::from_be_bytes([descriptor[last_tag + 6], descriptor[last_tag + 7]]);
+                descriptor[last_tag + 6..last_tag + 8]
+                    .copy_from_slice(&(flags | FLAG_LAST_TAG as u16).to_be_bytes());
+            }
+            records.push(descriptor);
+            records.extend(copies);
+        }
+
+        let mut commit = vec![0u8; self.block_size];
+        header(&mut commit, COMMIT_BLOCK, sequence);
+        commit[0x30..0x38].copy_from_slice(&now.to_be_bytes());
+        if csum {
+            let crc = crc32c(seed, &commit);
+            put_be32(&mut commit, 0x10, crc);
+        }
+        records.push(commit);
+
+        let first = self.first();
+        if u64::from(first) + records.len() as u64 > u64::from(self.last()) {
+            return Err(Error::ResourceLimit(format!(
+                "{} journal blocks needed, the journal has {}",
+                records.len(),
+                self.last() - first
+            )));
+        }
+        Ok(records
+            .into_iter()
+            .enumerate()
+            .map(|(i, record)| (self.physical(first + i as u32), record))
+            .collect())
+    }
+
+    /// Where the transaction starts, and its ID
+    pub fn start(&self) -> (u32, u32) {
+        (self.first(), self.sequence())
+    }
+
+    /// Journal block 0 after the transaction was checkpointed
+    pub fn finish(&mut self) -> Vec<u8> {
+        self.superblock = self.superblock(0, self.sequence().wrapping_add(1));
+        self.superblock.clone()
+    }
+}
+
+impl Logged {
+    /// Put back the magic an escaped copy had its first bytes cleared of
+    pub fn unescape(&self, data: &mut [u8]) {
+        if self.escaped {
+            put_be32(data, 0, MAGIC);
+        }
+    }
+}
+
+/// Every block copy left in the journal whose first block is `superblock`,
+/// reading journal block `index` with `read`
+///
+/// The whole log is read, not just from its start: checkpointed
+/// transactions are where older versions of metadata survive. Copies a
+/// newer transaction has partly overwritten are left out.
+pub(super) fn logged_blocks(
+    superblock: &[u8],
+    mut read: impl FnMut(u32) -> Result<Vec<u8>>,
+) -> Result<Vec<Logged>> {
+    let block_type = be_u32(superblock, 4);
+    if be_u32(superblock, 0) != MAGIC || !matches!(block_type, SUPERBLOCK_V1 | SUPERBLOCK_V2) {
+        return Err(Error::InvalidFormat(
+            "corrupt journal superblock".to_string(),
+        ));
+    }
+    let incompat = if block_type == SUPERBLOCK_V2 {
+        be_u32(superblock, 0x28)
+    } else {
+        0
+    };
+    let wide = incompat & INCOMPAT_64BIT != 0;
+    let tag_size = if incompat & INCOMPAT_CSUM_V3 != 0 {
+        16
+    } else {
+        let size = if incompat & INCOMPAT_CSUM_V2 != 0 {
+            14
+        } else {
+            12
+        };
+        if wide {
+            size
+        } else {
+            size - 4
+        }
+    };
+    let tail = if incompat & (INCOMPAT_CSUM_V2 | INCOMPAT_CSUM_V3) != 0 {
+        4
+    } else {
+        0
+    };
+    let (first, last) = (be_u32(superblock, 0x14), be_u32(superblock, 0x10));
+    if first == 0 || first >= last {
+        return Err(Error::InvalidFormat(
+            "corrupt journal superblock".to_string(),
+        ));
+    }
+    let next = |index: u32| if index + 1 >= last { first } else { index + 1 };
+
+    // Which journal blocks are journal headers, and the descriptors
+    let mut header = vec![false; last as usize];
+    let mut descriptors = Vec::new();
+    for index in first..last {
+        let block = read(index)?;
+        if be_u32(&block, 0) == MAGIC {
+            header[index as usize] = true;
+            if be_u32(&block, 4) == DESCRIPTOR_BLOCK {
+                descriptors.push((index, block));
+            }
+        }
+    }
+
+    let mut logged = Vec::new();
+    for (index, descriptor) in descriptors {
+        let sequence = be_u32(&descriptor, 8);
+        let end = descriptor.len() - tail;
+        let mut found = Vec::new();
+        let mut offset = HEADER_SIZE;
+        let mut at = index;
+        while offset + tag_size <= end {
+            let tag = &descriptor[offset..offset + tag_size];
+            let flags = if tag_size == 16 {
+                be_u32(tag, 4)
+            } else {
+                u32::from(u16::from_be_bytes([tag[6], tag[7]]))
+            };
+            let high = if wide { u64::from(be_u32(tag, 8)) } else { 0 };
+            at = next(at);
+            found.push(Logged {
+                block: high << 32 | u64::from(be_u32(tag, 0)),
+                sequence,
+                index: at,
+                escaped: flags & FLAG_ESCAPE != 0,
+            });
+            offset += tag_size;
+            if flags & FLAG_SAME_UUID == 0 {
+                offset += UUID_SIZE;
+            }
+            if flags & FLAG_LAST_TAG != 0 {
+                break;
+            }
+        }
+        // Escaped copies never start with the magic, so one that does is
+        // a block of a newer transaction
+        if found.iter().all(|copy| !header[copy.index as usize]) {
+            logged.extend(found);
+        }
+    }
+    Ok(logged)
+}
@@ -0,0 +1,506 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Superblock, group descriptors and metadata checksums
+
+use crate::core::{Error, Result};
+use crate::disk::vhdx::CRC32C_TABLE;
+
+pub(super) const SUPERBLOCK_OFFSET: u64 = 1024;
+pub(super) const SUPERBLOCK_SIZE: usize = 1024;
+const MAGIC: u16 = 0xef53;
+
+/// Superblock state: errors were detected
+const STATE_ERROR: u16 = 2;
+
+pub(super) const COMPAT_HAS_JOURNAL: u32 = 0x4;
+
+pub(super) const INCOMPAT_FILETYPE: u32 = 0x2;
+pub(super) const INCOMPAT_RECOVER: u32 = 0x4;
+pub(super) const INCOMPAT_EXTENTS: u32 = 0x40;
+pub(super) const INCOMPAT_64BIT: u32 = 0x80;
+const INCOMPAT_MMP: u32 = 0x100;
+const INCOMPAT_FLEX_BG: u32 = 0x200;
+const INCOMPAT_EA_INODE: u32 = 0x400;
+const INCOMPAT_CSUM_SEED: u32 = 0x2000;
+const INCOMPAT_LARGEDIR: u32 = 0x4000;
+const INCOMPAT_INLINE_DATA: u32 = 0x8000;
+const INCOMPAT_ENCRYPT: u32 = 0x10000;
+const INCOMPAT_CASEFOLD: u32 = 0x20000;
+
+/// Incompatible features the reader understands; not compression,
+/// meta_bg, dirdata or journal devices
+const INCOMPAT_READ: u32 = INCOMPAT_FILETYPE
+    | INCOMPAT_RECOVER
+    | INCOMPAT_EXTENTS
+    | INCOMPAT_64BIT
+    | INCOMPAT_MMP
+    | INCOMPAT_FLEX_BG
+    | INCOMPAT_EA_INODE
+    | INCOMPAT_CSUM_SEED
+    | INCOMPAT_LARGEDIR
+    | INCOMPAT_INLINE_DATA
+    | INCOMPAT_ENCRYPT
+    | INCOMPAT_CASEFOLD;
+/// Incompatible features the writer keeps consistent
+const INCOMPAT_WRITE: u32 = INCOMPAT_FILETYPE
+    | INCOMPAT_EXTENTS
+    | INCOMPAT_64BIT
+    | INCOMPAT_FLEX_BG
+    | INCOMPAT_CSUM_SEED
+    | INCOMPAT_LARGEDIR;
+
+const RO_COMPAT_SPARSE_SUPER: u32 = 0x1;
+pub(super) const RO_COMPAT_LARGE_FILE: u32 = 0x2;
+pub(super) const RO_COMPAT_HUGE_FILE: u32 = 0x8;
+const RO_COMPAT_GDT_CSUM: u32 = 0x10;
+pub(super) const RO_COMPAT_DIR_NLINK: u32 = 0x20;
+const RO_COMPAT_EXTRA_ISIZE: u32 = 0x40;
+const RO_COMPAT_METADATA_CSUM: u32 = 0x400;
+const RO_COMPAT_PROJECT: u32 = 0x2000;
+const RO_COMPAT_VERITY: u32 = 0x8000;
+/// Read-only compatible features the writer keeps consistent; not quota,
+/// bigalloc or pending orphans
+const RO_COMPAT_WRITE: u32 = RO_COMPAT_SPARSE_SUPER
+    | RO_COMPAT_LARGE_FILE
+    | RO_COMPAT_HUGE_FILE
+    | RO_COMPAT_GDT_CSUM
+    | RO_COMPAT_DIR_NLINK
+    | RO_COMPAT_EXTRA_ISIZE
+    | RO_COMPAT_METADATA_CSUM
+    | RO_COMPAT_PROJECT
+    | RO_COMPAT_VERITY;
+
+/// Directory hashes of the htree index, see `s_def_hash_version`
+const FLAGS_UNSIGNED_HASH: u32 = 0x2;
+
+/// Group descriptor flags
+pub(super) const BG_INODE_UNINIT: u16 = 0x1;
+pub(super) const BG_BLOCK_UNINIT: u16 = 0x2;
+
+/// Descriptors this large carry the high halves of their fields
+const DESC_SIZE_64BIT: usize = 64;
+
+pub(super) fn le_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(buf[offset..offset + 2].try_into().unwrap())
+}
+
+pub(super) fn le_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+pub(super) fn put_u16(buf: &mut [u8], offset: usize, value: u16) {
+    buf[offset..offset + 2].copy_from_slice(&value.to_le_bytes());
+}
+
+pub(super) fn put_u32(buf: &mut [u8], offset: usize, value: u32) {
+    buf[offset..offset + 4].copy_from_slice(&value.to_le_bytes());
+}
+
+/// CRC-32C as ext4 and jbd2 use it: seeded, without the final inversion
+pub(super) fn crc32c(seed: u32, data: &[u8]) -> u32 {
+    data.iter().fold(seed, |crc, &byte| {
+        CRC32C_TABLE[((crc ^ u32::from(byte)) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// CRC-16 (ANSI, reflected) of the older `uninit_bg` descriptor checksums
+fn crc16(seed: u16, data: &[u8]) -> u16 {
+    data.iter().fold(seed, |mut crc, &byte| {
+        crc ^= u16::from(byte);
+        for _ in 0..8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0xa001
+            } else {
+                crc >> 1
+            };
+        }
+        crc
+    })
+}
+
+/// The primary superblock, with the values derived from it
+#[derive(Clone)]
+pub(super) struct Superblock {
+    pub raw: Vec<u8>,
+    pub block_size: u64,
+    pub blocks_count: u64,
+    pub first_data_block: u64,
+    pub blocks_per_group: u64,
+    pub inodes_per_group: u32,
+    pub inodes_count: u32,
+    pub inode_size: usize,
+    pub desc_size: usize,
+    pub group_count: u32,
+    /// Seed of the metadata checksums
+    pub csum_seed: u32,
+}
+
+impl Superblock {
+    pub fn parse(raw: Vec<u8>) -> Result<Self> {
+        if le_u16(&raw, 0x38) != MAGIC {
+            return Err(Error::InvalidFormat(
+                "not an ext2/3/4 filesystem".to_string(),
+            ));
+        }
+        let log_block_size = le_u32(&raw, 0x18);
+        if log_block_size > 6 {
+            return Err(Error::InvalidFormat(format!(
+                "invalid block size 2^{}",
+                log_block_size + 10
+            )));
+        }
+        let mut sb = Self {
+            block_size: 1024 << log_block_size,
+            blocks_count: u64::from(le_u32(&raw, 0x4)),
+            first_data_block: u64::from(le_u32(&raw, 0x14)),
+            blocks_per_group: u64::from(le_u32(&raw, 0x20)),
+            inodes_per_group: le_u32(&raw, 0x28),
+            inodes_count: le_u32(&raw, 0x0),
+            // Revision 0 filesystems have 128-byte inodes
+            inode_size: if le_u32(&raw, 0x4c) == 0 {
+                128
+            } else {
+                usize::from(le_u16(&raw, 0x58))
+            },
+            desc_size: 32,
+            group_count: 0,
+            csum_seed: 0,
+            raw,
+        };
+        if sb.incompat() & INCOMPAT_64BIT != 0 {
+            sb.blocks_count |= u64::from(le_u32(&sb.raw, 0x150)) << 32;
+            sb.desc_size = usize::from(le_u16(&sb.raw, 0xfe));
+        }
+        if sb.blocks_per_group == 0
+            || sb.blocks_per_group > sb.block_size * 8
+            || sb.inodes_per_group == 0
+            || u64::from(sb.inodes_per_group) > sb.block_size * 8
+            || sb.inode_size < 128
+            || !sb.inode_size.is_power_of_two()
+            || sb.inode_size as u64 > sb.block_size
+            || sb.desc_size < 32
+            || !sb.desc_size.is_power_of_two()
+            || sb.first_data_block >= sb.blocks_count
+        {
+            return Err(Error::InvalidFormat("corrupt superblock".to_string()));
+        }
+        sb.group_count =
+            (sb.blocks_count - sb.first_data_block).div_ceil(sb.blocks_per_group) as u32;
+        sb.csum_seed = if sb.incompat() & INCOMPAT_CSUM_SEED != 0 {
+            le_u32(&sb.raw, 0x270)
+        } else {
+            crc32c(!0, sb.uuid())
+        };
+        if sb.metadata_csum() && crc32c(!0, &sb.raw[..0x3fc]) != le_u32(&sb.raw, 0x3fc) {
+            return Err(Error::InvalidFormat(
+                "superblock checksum mismatch".to_string(),
+            ));
+        }
+        Ok(sb)
+    }
+
+    pub fn compat(&self) -> u32 {
+        le_u32(&self.raw, 0x5c)
+    }
+
+    pub fn incompat(&self) -> u32 {
+        le_u32(&self.raw, 0x60)
+    }
+
+    pub fn ro_compat(&self) -> u32 {
+        le_u32(&self.raw, 0x64)
+    }
+
+    pub fn uuid(&self) -> &[u8] {
+        &self.raw[0x68..0x78]
+    }
+
+    pub fn label(&self) -> String {
+        let name = &self.raw[0x78..0x88];
+        let end = name.iter().position(|&b| b == 0).unwrap_or(name.len());
+        String::from_utf8_lossy(&name[..end]).into_owned()
+    }
+
+    pub fn journal_inode(&self) -> u32 {
+        le_u32(&self.raw, 0xe0)
+    }
+
+    pub fn metadata_csum(&self) -> bool {
+        self.ro_compat() & RO_COMPAT_METADATA_CSUM != 0
+    }
+
+    /// Group descriptors, bitmaps and inode tables may be left uninitialized
+    pub fn uninit_groups(&self) -> bool {
+        self.ro_compat() & (RO_COMPAT_METADATA_CSUM | RO_COMPAT_GDT_CSUM) != 0
+    }
+
+    pub fn set_needs_recovery(&mut self, recover: bool) {
+        let incompat = if recover {
+            self.incompat() | INCOMPAT_RECOVER
+        } else {
+            self.incompat() & !INCOMPAT_RECOVER
+        };
+        put_u32(&mut self.raw, 0x60, incompat);
+    }
+
+    pub fn set_ro_compat(&mut self, feature: u32) {
+        let ro_compat = self.ro_compat() | feature;
+        put_u32(&mut self.raw, 0x64, ro_compat);
+    }
+
+    /// Hash of htree directories, as its unsigned variant where the
+    /// filesystem was made on a platform with unsigned chars
+    pub fn hash_version(&self, version: u8) -> u8 {
+        if version <= 2 && le_u32(&self.raw, 0x160) & FLAGS_UNSIGNED_HASH != 0 {
+            version + 3
+        } else {
+            version
+        }
+    }
+
+    pub fn hash_seed(&self) -> [u32; 4] {
+        std::array::from_fn(|i| le_u32(&self.raw, 0xec + 4 * i))
+    }
+
+    /// Bytes of the inode past the first 128 new inodes use
+    pub fn extra_isize(&self) -> u16 {
+        if self.inode_size <= 128 {
+            return 0;
+        }
+        let want = le_u16(&self.raw, 0x15e);
+        let want = if want == 0 { 32 } else { want };
+        want.min((self.inode_size - 128) as u16)
+    }
+
+    pub fn free_blocks(&self) -> u64 {
+        let mut free = u64::from(le_u32(&self.raw, 0xc));
+        if self.incompat() & INCOMPAT_64BIT != 0 {
+            free |= u64::from(le_u32(&self.raw, 0x158)) << 32;
+        }
+        free
+    }
+
+    pub fn set_free_blocks(&mut self, free: u64) {
+        put_u32(&mut self.raw, 0xc, free as u32);
+        if self.incompat() & INCOMPAT_64BIT != 0 {
+            put_u32(&mut self.raw, 0x158, (free >> 32) as u32);
+        }
+    }
+
+    pub fn free_inodes(&self) -> u32 {
+        le_u32(&self.raw, 0x10)
+    }
+
+    pub fn set_free_inodes(&mut self, free: u32) {
+        put_u32(&mut self.raw, 0x10, free);
+    }
+
+    pub fn set_write_time(&mut self, secs: u32) {
+        put_u32(&mut self.raw, 0x30, secs);
+    }
+
+    pub fn update_checksum(&mut self) {
+        if self.metadata_csum() {
+            let csum = crc32c(!0, &self.raw[..0x3fc]);
+            put_u32(&mut self.raw, 0x3fc, csum);
+        }
+    }
+
+    /// Features this module can't read, if any
+    pub fn unreadable_features(&self) -> u32 {
+        self.incompat() & !INCOMPAT_READ
+    }
+
+    /// Refuse writes that would damage this filesystem
+    pub fn check_writes(&self) -> Result<()> {
+        let incompat = self.incompat() & !INCOMPAT_WRITE;
+        let ro_compat = self.ro_compat() & !RO_COMPAT_WRITE;
+        if incompat & INCOMPAT_RECOVER != 0 {
+            Err(Error::InvalidState(
+                "the journal needs recovery (mounted, or not cleanly unmounted); run e2fsck first"
+                    .to_string(),
+            ))
+        } else if le_u16(&self.raw, 0x3a) & STATE_ERROR != 0 {
+            Err(Error::InvalidState(
+                "the filesystem has errors; run e2fsck first".to_string(),
+            ))
+        } else if self.incompat() & INCOMPAT_EXTENTS == 0 {
+            Err(Error::Unsupported(
+                "writes to filesystems without extents (ext2, ext3)".to_string(),
+            ))
+        } else if incompat != 0 || ro_compat != 0 {
+            Err(Error::Unsupported(format!(
+                "writes with features incompat 0x{:x}, ro_compat 0x{:x}",
+                incompat, ro_compat
+            )))
+        } else if self.compat() & COMPAT_HAS_JOURNAL != 0 && self.journal_inode() == 0 {
+            Err(Error::Unsupported(
+                "writes with an external journal".to_string(),
+            ))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Block of the group descriptor table holding group `group`, and the
+    /// offset in it
+    pub fn descriptor_location(&self, group: u32) -> (u64, usize) {
+        let offset = u64::from(group) * self.desc_size as u64;
+        (
+            self.first_data_block + 1 + offset / self.block_size,
+            (offset % self.block_size) as usize,
+        )
+    }
+
+    /// Blocks of group `group`
+    pub fn group_blocks(&self, group: u32) -> u64 {
+        let start = self.first_data_block + u64::from(group) * self.blocks_per_group;
+        (self.blocks_count - start).min(self.blocks_per_group)
+    }
+}
+
+/// The group descriptor table
+#[derive(Clone)]
+pub(super) struct Groups {
+    pub raw: Vec<u8>,
+    desc_size: usize,
+}
+
+impl Groups {
+    pub fn new(raw: Vec<u8>, desc_size: usize) -> Self {
+        Self { raw, desc_size }
+    }
+
+    pub fn desc(&self, group: u32) -> &[u8] {
+        let start = group as usize * self.desc_size;
+        &self.raw[start..start + self.desc_size]
+    }
+
+    fn desc_mut(&mut self, group: u32) -> &mut [u8] {
+        let start = group as usize * self.desc_size;
+        &mut self.raw[start..start + self.desc_size]
+    }
+
+    /// A field split in a low half at `lo` and a high half at `hi`
+    fn get(&self, group: u32, lo: usize, hi: usize, width: usize) -> u64 {
+        let desc = self.desc(group);
+        let read = |offset| match width {
+            2 => u64::from(le_u16(desc, offset)),
+            _ => u64::from(le_u32(desc, offset)),
+        };
+        let mut value = read(lo);
+        if self.desc_size >= DESC_SIZE_64BIT {
+            value |= read(hi) << (8 * width);
+        }
+        value
+    }
+
+    fn set(&mut self, group: u32, lo: usize, hi: usize, width: usize, value: u64) {
+        let wide = self.desc_size >= DESC_SIZE_64BIT;
+        let desc = self.desc_mut(group);
+        let mut write = |offset, value: u64| match width {
+            2 => put_u16(desc, offset, value as u16),
+            _ => put_u32(desc, offset, value as u32),
+        };
+        write(lo, value);
+        if wide {
+            write(hi, value >> (8 * width));
+        }
+    }
+
+    pub fn block_bitmap(&self, group: u32) -> u64 {
+        self.get(group, 0x0, 0x20, 4)
+    }
+
+    pub fn inode_bitmap(&self, group: u32) -> u64 {
+        self.get(group, 0x4, 0x24, 4)
+    }
+
+    pub fn inode_table(&self, group: u32) -> u64 {
+        self.get(group, 0x8, 0x28, 4)
+    }
+
+    pub fn free_blocks(&self, group: u32) -> u64 {
+        self.get(group, 0xc, 0x2c, 2)
+    }
+
+    pub fn set_free_blocks(&mut self, group: u32, free: u64) {
+        self.set(group, 0xc, 0x2c, 2, free);
+    }
+
+    pub fn free_inodes(&self, group: u32) -> u64 {
+        self.get(group, 0xe, 0x2e, 2)
+    }
+
+    pub fn set_free_inodes(&mut self, group: u32, free: u64) {
+        self.set(group, 0xe, 0x2e, 2, free);
+    }
+
+    pub fn used_dirs(&self, group: u32) -> u64 {
+        self.get(group, 0x10, 0x30, 2)
+    }
+
+    pub fn set_used_dirs(&mut self, group: u32, dirs: u64) {
+        self.set(group, 0x10, 0x30, 2, dirs);
+    }
+
+    pub fn flags(&self, group: u32) -> u16 {
+        le_u16(self.desc(group), 0x12)
+    }
+
+    pub fn set_flags(&mut self, group: u32, flags: u16) {
+        put_u16(self.desc_mut(group), 0x12, flags);
+    }
+
+    pub fn itable_unused(&self, group: u32) -> u64 {
+        self.get(group, 0x1c, 0x32, 2)
+    }
+
+    pub fn set_itable_unused(&mut self, group: u32, unused: u64) {
+        self.set(group, 0x1c, 0x32, 2, unused);
+    }
+
+    pub fn set_block_bitmap_csum(&mut self, group: u32, csum: u32) {
+        self.set(group, 0x18, 0x38, 2, u64::from(csum));
+    }
+
+    pub fn set_inode_bitmap_csum(&mut self, group: u32, csum: u32) {
+        self.set(group, 0x1a, 0x3a, 2, u64::from(csum));
+    }
+
+    pub fn update_checksum(&mut self, group: u32, sb: &Superblock) {
+        let desc_size = self.desc_size;
+        let le_group = group.to_le_bytes();
+        let desc = self.desc_mut(group);
+        let csum = if sb.metadata_csum() {
+            let crc = crc32c(sb.csum_seed, &le_group);
+            let crc = crc32c(crc, &desc[..0x1e]);
+            let crc = crc32c(crc, &[0, 0]);
+            (crc32c(crc, &desc[0x20..desc_size]) & 0xffff) as u16
+        } else if sb.uninit_groups() {
+            let crc = crc16(!0, sb.uuid());
+            let crc = crc16(crc, &le_group);
+            let crc = crc16(crc, &desc[..0x1e]);
+            if desc_size > 0x20 {
+                crc16(crc, &desc[0x20..desc_size])
+            } else {
+                crc
+            }
+        } else {
+            return;
+        };
+        put_u16(desc, 0x1e, csum);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_checksums() {
+        // Check values of the CRC-32C and CRC-16/ARC catalogues, which
+        // invert around the raw register ext4 keeps
+        assert_eq!(!crc32c(!0, b"123456789"), 0xe306_9283);
+        assert_eq!(crc16(0, b"123456789"), 0xbb3d);
+    }
+}
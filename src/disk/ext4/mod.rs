@@ -0,0 +1,983 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Pure Rust ext4 reader and writer
+//!
+//! Reads ext2, ext3 and ext4 filesystems in disk images, and changes ext4
+//! ones without mounting them: files can be written, truncated and
+//! removed, directories created and modes changed. This lets fixers run
+//! where libguestfs and its appliance are not available.
+//!
+//! Every change is a transaction. Metadata goes through the filesystem's
+//! journal the way the kernel commits it, file data to newly allocated
+//! blocks before that, so a crash leaves either the old or the new state
+//! once `e2fsck` or the next mount replays the journal. Filesystems
+//! without a journal are written in place.
+//!
+//! Deleted files can be found and read back, from block maps left in the
+//! inode table or the journal; see [`Ext4::deleted`].
+//!
+//! Writes are refused for filesystems that need recovery or have errors,
+//! and for features this module doesn't keep consistent: no extents
+//! (ext2, ext3), inline data, encryption, casefolding, quota, bigalloc,
+//! meta_bg and external journals among them.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use guestkit::disk::Ext4;
+//!
+//! // The root filesystem of a raw image, in a partition at 1 MiB
+//! let mut fs = Ext4::open_rw("/srv/images/web01.img", 1024 * 1024)?;
+//! fs.write_file("/etc/hostname", b"web01\n", 0o644)?;
+//! fs.mkdir("/root/.ssh", 0o700)?;
+//! fs.unlink("/etc/machine-id")?;
+//! # Ok::<(), guestkit::Error>(())
+//! ```
+
+mod alloc;
+mod dir;
+mod inode;
+mod jbd2;
+mod layout;
+mod undelete;
+
+use crate::core::{Error, Result};
+use crate::disk::qcow2::lock_for_write;
+use crate::disk::DiskReader;
+use dir::{FT_DIR, FT_REG_FILE};
+use inode::{push_run, Inode, Run, S_IFDIR, S_IFREG};
+use jbd2::{Journal, Records};
+use layout::{
+    crc32c, le_u32, put_u32, Groups, Superblock, COMPAT_HAS_JOURNAL, RO_COMPAT_DIR_NLINK,
+    RO_COMPAT_LARGE_FILE, SUPERBLOCK_OFFSET, SUPERBLOCK_SIZE,
+};
+use serde::Serialize;
+use std::collections::btree_map::Entry;
+use std::collections::{BTreeMap, BTreeSet};
+use std::fs::{File, OpenOptions};
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+use uuid::Uuid;
+
+/// Extended attribute blocks
+const XATTR_MAGIC: u32 = 0xea02_0000;
+
+/// Links a directory may have before it counts them no more (dir_nlink)
+const LINK_MAX: u16 = 65000;
+
+/// Largest size that fits the 32-bit size field of old kernels
+const MAX_SMALL_FILE: u64 = 0x7fff_ffff;
+
+/// File metadata, as `lstat` reports it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Ext4Stat {
+    pub ino: u32,
+    /// File type and permission bits
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    pub links: u32,
+    pub mtime: i64,
+}
+
+/// A directory entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Ext4DirEntry {
+    pub name: String,
+    pub ino: u32,
+    /// Type from the entry: 1 regular file, 2 directory, 7 symbolic link,
+    /// 0 if the filesystem doesn't record it
+    pub file_type: u8,
+}
+
+/// A deleted or orphaned file, as [`Ext4::deleted`] finds it
+#[derive(Debug, Clone, Serialize)]
+pub struct Ext4Deleted {
+    pub ino: u32,
+    /// Path from a directory entry the delete left behind, if one survives
+    pub path: Option<String>,
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    pub mtime: i64,
+    /// When it was deleted; 0 for orphans
+    pub dtime: i64,
+    /// Unlinked while open: its blocks are still allocated to it
+    pub orphan: bool,
+    /// Journal transaction whose copy of the inode maps the data, if the
+    /// inode itself no longer does
+    pub sequence: Option<u32>,
+    pub blocks: u64,
+    /// Blocks allocated again since the delete, now holding other data
+    pub blocks_reused: u64,
+    #[serde(skip)]
+    runs: Vec<Run>,
+    #[serde(skip)]
+    raw: Vec<u8>,
+    /// Extent tree blocks as the journal had them
+    #[serde(skip)]
+    tree: BTreeMap<u64, Vec<u8>>,
+}
+
+/// Blocks a change has modified, and the groups whose counts it changed
+///
+/// Reads go through the transaction so a change sees its own writes.
+#[derive(Default)]
+struct Tx {
+    blocks: BTreeMap<u64, Vec<u8>>,
+    groups: BTreeSet<u32>,
+    block_bitmaps: BTreeSet<u32>,
+    inode_bitmaps: BTreeSet<u32>,
+    /// Blocks freed by this transaction; not reused until it commits
+    freed: BTreeSet<u64>,
+}
+
+/// Where the filesystem is: a raw image or device opened for writing, or
+/// any image through the disk reader
+enum Volume {
+    File(File),
+    Reader(Box<DiskReader>),
+}
+
+impl Volume {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        match self {
+            Volume::File(file) => file.read_exact_at(buf, offset).map_err(Error::Io),
+            Volume::Reader(reader) => reader.pread_exact(offset, buf),
+        }
+    }
+
+    fn write_at(&self, offset: u64, buf: &[u8]) -> Result<()> {
+        match self {
+            Volume::File(file) => file.write_all_at(buf, offset).map_err(Error::Io),
+            Volume::Reader(_) => Err(Error::Unsupported(
+                "writes through the disk image reader".to_string(),
+            )),
+        }
+    }
+
+    fn sync(&self) -> Result<()> {
+        match self {
+            Volume::File(file) => file.sync_data().map_err(Error::Io),
+            Volume::Reader(_) => Ok(()),
+        }
+    }
+}
+
+/// An ext2/3/4 filesystem at `offset` of a disk image
+pub struct Ext4 {
+    volume: Volume,
+    offset: u64,
+    sb: Superblock,
+    groups: Groups,
+    journal: Option<Journal>,
+    writable: bool,
+    /// A commit failed half way; the on-disk state is only consistent
+    /// again after journal replay
+    poisoned: bool,
+}
+
+impl Ext4 {
+    /// Open the filesystem at `offset` of a raw image or device, read-only
+    pub fn open<P: AsRef<Path>>(path: P, offset: u64) -> Result<Self> {
+        let file = File::open(path.as_ref())?;
+        Self::load(Volume::File(file), offset, false)
+    }
+
+    /// Open the filesystem at `offset` of a raw image or device for writing
+    ///
+    /// Refuses images a VM has open, and filesystems that need recovery
+    /// (mounted, or not cleanly unmounted) or use features the writer
+    /// doesn't support.
+    pub fn open_rw<P: AsRef<Path>>(path: P, offset: u64) -> Result<Self> {
+        let path = path.as_ref();
+        let file = OpenOptions::new().read(true).write(true).open(path)?;
+        lock_for_write(&file, path)?;
+        Self::load(Volume::File(file), offset, true)
+    }
+
+    /// Read the filesystem at `offset` of any image the disk reader opens
+    pub fn from_reader(reader: DiskReader, offset: u64) -> Result<Self> {
+        Self::load(Volume::Reader(Box::new(reader)), offset, false)
+    }
+
+    fn load(volume: Volume, offset: u64, writable: bool) -> Result<Self> {
+        let mut raw = vec![0u8; SUPERBLOCK_SIZE];
+        volume.read_at(offset + SUPERBLOCK_OFFSET, &mut raw)?;
+        let sb = Superblock::parse(raw)?;
+        let unreadable = sb.unreadable_features();
+        if unreadable != 0 {
+            return Err(Error::Unsupported(format!(
+                "ext4 incompatible features 0x{:x}",
+                unreadable
+            )));
+        }
+
+        let table_size = u64::from(sb.group_count) * sb.desc_size as u64;
+        if table_size > sb.blocks_count.saturating_mul(sb.block_size) / 2 {
+            return Err(Error::InvalidFormat("corrupt superblock".to_string()));
+        }
+        let mut raw = vec![0u8; table_size as usize];
+        let (table, _) = sb.descriptor_location(0);
+        volume.read_at(offset + table * sb.block_size, &mut raw)?;
+        let groups = Groups::new(raw, sb.desc_size);
+
+        let mut fs = Self {
+            volume,
+            offset,
+            sb,
+            groups,
+            journal: None,
+            writable: false,
+            poisoned: false,
+        };
+        if writable {
+            fs.sb.check_writes()?;
+            fs.journal = fs.load_journal()?;
+            fs.writable = true;
+        }
+        Ok(fs)
+    }
+
+    fn load_journal(&self) -> Result<Option<Journal>> {
+        if self.sb.compat() & COMPAT_HAS_JOURNAL == 0 {
+            log::warn!("ext4: no journal, changes are written in place");
+            return Ok(None);
+        }
+        let tx = Tx::default();
+        let inode = self.read_inode(&tx, self.sb.journal_inode())?;
+        let (runs, _) = self.map(&tx, &inode)?;
+        let first = runs
+            .first()
+            .filter(|run| run.logical == 0)
+            .ok_or_else(|| Error::InvalidFormat("the journal has no superblock".to_string()))?;
+        let superblock = self.read_block(&tx, first.physical)?;
+        Journal::load(runs, superblock).map(Some)
+    }
+
+    pub fn label(&self) -> String {
+        self.sb.label()
+    }
+
+    pub fn uuid(&self) -> Uuid {
+        Uuid::from_slice(self.sb.uuid()).unwrap_or_default()
+    }
+
+    pub fn block_size(&self) -> u64 {
+        self.sb.block_size
+    }
+
+    /// Whether the filesystem was opened for writing
+    pub fn is_writable(&self) -> bool {
+        self.writable
+    }
+
+    /// Metadata of `path`, not following a final symbolic link
+    pub fn stat(&self, path: &str) -> Result<Ext4Stat> {
+        let inode = self.resolve(&Tx::default(), path, false)?;
+        Ok(Ext4Stat {
+            ino: inode.ino,
+            mode: u32::from(inode.mode()),
+            uid: inode.uid(),
+            gid: inode.gid(),
+            size: inode.size(),
+            links: u32::from(inode.links()),
+            mtime: i64::from(inode.mtime()),
+        })
+    }
+
+    pub fn exists(&self, path: &str) -> Result<bool> {
+        match self.resolve(&Tx::default(), path, false) {
+            Ok(_) => Ok(true),
+            Err(Error::NotFound(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn read_file(&self, path: &str) -> Result<Vec<u8>> {
+        let tx = Tx::default();
+        let inode = self.resolve(&tx, path, true)?;
+        if !inode.is_reg() {
+            return Err(Error::InvalidOperation(format!(
+                "{}: not a regular file",
+                path
+            )));
+        }
+        self.read_data(&tx, &inode)
+    }
+
+    pub fn read_link(&self, path: &str) -> Result<String> {
+        let tx = Tx::default();
+        let inode = self.resolve(&tx, path, false)?;
+        if !inode.is_symlink() {
+            return Err(Error::InvalidOperation(format!(
+                "{}: not a symbolic link",
+                path
+            )));
+        }
+        let target = self.link_target(&tx, &inode)?;
+        Ok(String::from_utf8_lossy(&target).into_owned())
+    }
+
+    /// Entries of directory `path`, "." and ".." included
+    pub fn list_dir(&self, path: &str) -> Result<Vec<Ext4DirEntry>> {
+        let tx = Tx::default();
+        let dir = self.resolve(&tx, path, true)?;
+        if !dir.is_dir() {
+            return Err(Error::NotFound(format!("{}: not a directory", path)));
+        }
+        Ok(self
+            .entries(&tx, &dir)?
+            .into_iter()
+            .map(|entry| Ext4DirEntry {
+                name: String::from_utf8_lossy(&entry.name).into_owned(),
+                ino: entry.inode,
+                file_type: entry.file_type,
+            })
+            .collect())
+    }
+
+    /// Create or replace the regular file `path`
+    ///
+    /// `mode` sets the permissions of a new file; an existing file keeps
+    /// its own. A final symbolic link is followed.
+    pub fn write_file(&mut self, path: &str, content: &[u8], mode: u32) -> Result<()> {
+        self.change(|fs, tx| {
+            let now = now();
+            let (mut parent, name) = fs.resolve_parent(tx, path)?;
+            let mut inode = match fs.lookup(tx, &parent, name.as_bytes())? {
+                Some(entry) => {
+                    let inode = fs.read_inode(tx, entry.inode)?;
+                    if inode.is_symlink() {
+                        fs.resolve(tx, path, true)?
+                    } else {
+                        inode
+                    }
+                }
+                None => {
+                    let ino = fs.alloc_inode(tx, parent.ino, false)?;
+                    fs.add_entry(tx, &mut parent, name.as_bytes(), ino, FT_REG_FILE)?;
+                    parent.set_modified(now);
+                    fs.write_inode(tx, &mut parent)?;
+                    Inode::new(ino, S_IFREG | permissions(mode), 1, now, &fs.sb)
+                }
+            };
+            if !inode.is_reg() {
+                return Err(Error::InvalidOperation(format!(
+                    "{}: not a regular file",
+                    path
+                )));
+            }
+            fs.replace_data(tx, &mut inode, content)?;
+            inode.set_modified(now);
+            fs.write_inode(tx, &mut inode)
+        })
+    }
+
+    /// Create directory `path`; its parent must exist
+    pub fn mkdir(&mut self, path: &str, mode: u32) -> Result<()> {
+        self.change(|fs, tx| {
+            let now = now();
+            let (mut parent, name) = fs.resolve_parent(tx, path)?;
+            if fs.lookup(tx, &parent, name.as_bytes())?.is_some() {
+                return Err(Error::InvalidOperation(format!("{}: already exists", path)));
+            }
+            let ino = fs.alloc_inode(tx, parent.ino, true)?;
+            let mut dir = Inode::new(ino, S_IFDIR | permissions(mode), 2, now, &fs.sb);
+            let (block, _) = fs.alloc_blocks(tx, fs.inode_goal(ino), 1)?[0];
+            tx.blocks.insert(block, fs.new_dir_block(&dir, parent.ino));
+            let run = Run {
+                logical: 0,
+                physical: block,
+                len: 1,
+                uninit: false,
+            };
+            fs.set_runs(tx, &mut dir, &[run])?;
+            dir.set_size(fs.sb.block_size);
+            fs.write_inode(tx, &mut dir)?;
+
+            fs.add_entry(tx, &mut parent, name.as_bytes(), ino, FT_DIR)?;
+            // Past the limit directories count 1 link, whatever they have
+            match parent.links() {
+                1 => {}
+                links if links + 1 < LINK_MAX => parent.set_links(links + 1),
+                _ => {
+                    fs.sb.set_ro_compat(RO_COMPAT_DIR_NLINK);
+                    parent.set_links(1);
+                }
+            }
+            parent.set_modified(now);
+            fs.write_inode(tx, &mut parent)
+        })
+    }
+
+    /// Remove the file, symbolic link or device node `path`
+    pub fn unlink(&mut self, path: &str) -> Result<()> {
+        self.change(|fs, tx| {
+            let now = now();
+            let (mut parent, name) = fs.resolve_parent(tx, path)?;
+            let entry = fs
+                .lookup(tx, &parent, name.as_bytes())?
+                .ok_or_else(|| Error::NotFound(path.to_string()))?;
+            let mut inode = fs.read_inode(tx, entry.inode)?;
+            if inode.is_dir() {
+                return Err(Error::InvalidOperation(format!("{}: is a directory", path)));
+            }
+            fs.remove_entry(tx, &parent, name.as_bytes())?;
+            parent.set_modified(now);
+            fs.write_inode(tx, &mut parent)?;
+
+            if inode.links() > 1 {
+                inode.set_links(inode.links() - 1);
+                inode.set_ctime(now);
+                fs.write_inode(tx, &mut inode)
+            } else {
+                fs.release_inode(tx, &mut inode, now)
+            }
+        })
+    }
+
+    /// Set the permission bits of `path`, following symbolic links
+    pub fn chmod(&mut self, path: &str, mode: u32) -> Result<()> {
+        self.change(|fs, tx| {
+            let mut inode = fs.resolve(tx, path, true)?;
+            inode.set_mode(inode.file_type() | permissions(mode));
+            inode.set_ctime(now());
+            fs.write_inode(tx, &mut inode)
+        })
+    }
+
+    /// Shrink or extend the regular file `path` to `size` bytes; extending
+    /// leaves a hole
+    pub fn truncate(&mut self, path: &str, size: u64) -> Result<()> {
+        self.change(|fs, tx| {
+            let mut inode = fs.resolve(tx, path, true)?;
+            if !inode.is_reg() {
+                return Err(Error::InvalidOperation(format!(
+                    "{}: not a regular file",
+                    path
+                )));
+            }
+            let block_size = fs.sb.block_size;
+            let keep = logical_blocks(size, block_size)?;
+            let (runs, _) = fs.map(tx, &inode)?;
+            let mut kept = Vec::new();
+            let mut cut = Vec::new();
+            for run in runs {
+                if run.logical >= keep {
+                    cut.push((run.physical, run.len));
+                } else if run.logical + run.len > keep {
+                    let len = keep - run.logical;
+                    kept.push(Run { len, ..run });
+                    cut.push((run.physical + u64::from(len), run.len - len));
+                } else {
+                    kept.push(run);
+                }
+            }
+
+            // The rest of the last block must read as zeros if the file
+            // grows again
+            let tail = (size % block_size) as usize;
+            if tail != 0 && size < inode.size() {
+                let last = (size / block_size) as u32;
+                if let Some(run) = kept
+                    .iter()
+                    .find(|run| !run.uninit && run.logical <= last && last < run.logical + run.len)
+                {
+                    let block = run.physical + u64::from(last - run.logical);
+                    let zeros = vec![0u8; block_size as usize - tail];
+                    fs.volume
+                        .write_at(fs.offset + block * block_size + tail as u64, &zeros)?;
+                }
+            }
+
+            fs.set_runs(tx, &mut inode, &kept)?;
+            for (start, len) in cut {
+                fs.free_blocks(tx, start, u64::from(len))?;
+            }
+            fs.set_size(&mut inode, size);
+            inode.set_modified(now());
+            fs.write_inode(tx, &mut inode)
+        })
+    }
+
+    /// Run `op` as one transaction, committing what it changed
+    ///
+    /// If `op` fails nothing is written and the in-memory state is rolled
+    /// back.
+    fn change<T>(&mut self, op: impl FnOnce(&mut Self, &mut Tx) -> Result<T>) -> Result<T> {
+        if !self.writable {
+            return Err(Error::PermissionDenied(
+                "ext4 filesystem opened read-only".to_string(),
+            ));
+        }
+        if self.poisoned {
+            return Err(Error::InvalidState(
+                "an earlier ext4 commit failed; run e2fsck before writing again".to_string(),
+            ));
+        }
+        let sb = self.sb.clone();
+        let groups = self.groups.clone();
+        let mut tx = Tx::default();
+        let prepared = op(self, &mut tx).and_then(|value| {
+            let records = self.prepare(&mut tx)?;
+            Ok((value, records))
+        });
+        let (value, records) = match prepared {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                self.sb = sb;
+                self.groups = groups;
+                return Err(e);
+            }
+        };
+        if let Err(e) = self.commit(tx, records) {
+            self.poisoned = true;
+            return Err(e);
+        }
+        Ok(value)
+    }
+
+    /// Put checksums, group descriptors and the superblock into `tx`, and
+    /// build its journal records
+    fn prepare(&mut self, tx: &mut Tx) -> Result<Option<Records>> {
+        if self.sb.metadata_csum() {
+            let block_bytes = (self.sb.blocks_per_group / 8) as usize;
+            for &group in &tx.block_bitmaps {
+                let bitmap = self.read_block(tx, self.groups.block_bitmap(group))?;
+                let csum = crc32c(self.sb.csum_seed, &bitmap[..block_bytes]);
+                self.groups.set_block_bitmap_csum(group, csum);
+            }
+            let inode_bytes = (self.sb.inodes_per_group / 8) as usize;
+            for &group in &tx.inode_bitmaps {
+                let bitmap = self.read_block(tx, self.groups.inode_bitmap(group))?;
+                let csum = crc32c(self.sb.csum_seed, &bitmap[..inode_bytes]);
+                self.groups.set_inode_bitmap_csum(group, csum);
+            }
+        }
+        for group in std::mem::take(&mut tx.groups) {
+            self.groups.update_checksum(group, &self.sb);
+            let (block, offset) = self.sb.descriptor_location(group);
+            let desc = self.groups.desc(group).to_vec();
+            self.block_mut(tx, block)?[offset..offset + desc.len()].copy_from_slice(&desc);
+        }
+
+        self.sb.set_write_time(now());
+        self.sb.update_checksum();
+        let (block, offset) = self.superblock_location();
+        let raw = self.sb.raw.clone();
+        self.block_mut(tx, block)?[offset..offset + SUPERBLOCK_SIZE].copy_from_slice(&raw);
+
+        match &self.journal {
+            Some(journal) => journal.transaction(&tx.blocks, u64::from(now())).map(Some),
+            None => Ok(None),
+        }
+    }
+
+    /// Write a prepared transaction
+    ///
+    /// The journal records go first; once they are on disk the journal
+    /// and the superblock are marked as needing recovery, so a crash while
+    /// the blocks are written in place replays them. Then both are marked
+    /// clean again.
+    fn commit(&mut self, tx: Tx, records: Option<Records>) -> Result<()> {
+        let block_size = self.sb.block_size;
+        let (sb_block, sb_offset) = self.superblock_location();
+        let volume = &self.volume;
+        let offset = self.offset;
+        let write = |block: u64, data: &[u8]| volume.write_at(offset + block * block_size, data);
+
+        let (Some(journal), Some(records)) = (&mut self.journal, records) else {
+            for (&block, data) in &tx.blocks {
+                write(block, data)?;
+            }
+            return volume.sync();
+        };
+
+        for (block, record) in &records {
+            write(*block, record)?;
+        }
+        volume.sync()?;
+
+        let (start, sequence) = journal.start();
+        let mut recovering = self.sb.clone();
+        recovering.set_needs_recovery(true);
+        recovering.update_checksum();
+        write(journal.physical(0), &journal.superblock(start, sequence))?;
+        volume.write_at(offset + SUPERBLOCK_OFFSET, &recovering.raw)?;
+        volume.sync()?;
+
+        for (&block, data) in &tx.blocks {
+            if block == sb_block {
+                let mut data = data.clone();
+                data[sb_offset..sb_offset + SUPERBLOCK_SIZE].copy_from_slice(&recovering.raw);
+                write(block, &data)?;
+            } else {
+                write(block, data)?;
+            }
+        }
+        volume.sync()?;
+
+        write(journal.physical(0), &journal.finish())?;
+        volume.sync()?;
+        volume.write_at(offset + SUPERBLOCK_OFFSET, &self.sb.raw)?;
+        volume.sync()
+    }
+
+    fn superblock_location(&self) -> (u64, usize) {
+        (
+            SUPERBLOCK_OFFSET / self.sb.block_size,
+            (SUPERBLOCK_OFFSET % self.sb.block_size) as usize,
+        )
+    }
+
+    fn read_disk_block(&self, block: u64) -> Result<Vec<u8>> {
+        if block >= self.sb.blocks_count {
+            return Err(Error::InvalidFormat(format!(
+                "block {} is past the end of the filesystem",
+                block
+            )));
+        }
+        let mut data = vec![0u8; self.sb.block_size as usize];
+        self.volume
+            .read_at(self.offset + block * self.sb.block_size, &mut data)?;
+        Ok(data)
+    }
+
+    /// Block `block` as the transaction has it
+    fn read_block(&self, tx: &Tx, block: u64) -> Result<Vec<u8>> {
+        match tx.blocks.get(&block) {
+            Some(data) => Ok(data.clone()),
+            None => self.read_disk_block(block),
+        }
+    }
+
+    /// Block `block` to change in the transaction
+    fn block_mut<'t>(&self, tx: &'t mut Tx, block: u64) -> Result<&'t mut [u8]> {
+        Ok(match tx.blocks.entry(block) {
+            Entry::Occupied(entry) => entry.into_mut(),
+            Entry::Vacant(entry) => entry.insert(self.read_disk_block(block)?),
+        })
+    }
+
+    /// Content of a file or symbolic link; holes read as zeros
+    fn read_data(&self, tx: &Tx, inode: &Inode) -> Result<Vec<u8>> {
+        let size = inode.size();
+        let block_size = self.sb.block_size;
+        let len = usize::try_from(size)
+            .map_err(|_| Error::ResourceLimit(format!("inode {}: {} bytes", inode.ino, size)))?;
+        let mut data = vec![0u8; len];
+        let (runs, _) = self.map(tx, inode)?;
+        for run in runs.iter().filter(|run| !run.uninit) {
+            let start = u64::from(run.logical) * block_size;
+            if start >= size {
+                continue;
+            }
+            let end = (start + u64::from(run.len) * block_size).min(size);
+            let buf = &mut data[start as usize..end as usize];
+            self.volume
+                .read_at(self.offset + run.physical * block_size, buf)?;
+            let blocks = run.physical..run.physical + u64::from(run.len);
+            for (&block, copy) in tx.blocks.range(blocks) {
+                let at = ((block - run.physical) * block_size) as usize;
+                let n = buf.len().saturating_sub(at).min(copy.len());
+                buf[at..at + n].copy_from_slice(&copy[..n]);
+            }
+        }
+        Ok(data)
+    }
+
+    /// Write file data straight to blocks `block..`, before the transaction
+    /// allocating them commits; the last block is padded with zeros
+    fn write_data(&self, block: u64, data: &[u8]) -> Result<()> {
+        let block_size = self.sb.block_size as usize;
+        let at = self.offset + block * self.sb.block_size;
+        let whole = data.len() / block_size * block_size;
+        self.volume.write_at(at, &data[..whole])?;
+        if whole < data.len() {
+            let mut last = vec![0u8; block_size];
+            last[..data.len() - whole].copy_from_slice(&data[whole..]);
+            self.volume.write_at(at + whole as u64, &last)?;
+        }
+        Ok(())
+    }
+
+    /// Give `inode` new blocks holding `content`, freeing the old ones
+    fn replace_data(&mut self, tx: &mut Tx, inode: &mut Inode, content: &[u8]) -> Result<()> {
+        let block_size = self.sb.block_size;
+        let count = logical_blocks(content.len() as u64, block_size)?;
+        let (old, _) = self.map(tx, inode)?;
+        let allocated = match count {
+            0 => Vec::new(),
+            count => self.alloc_blocks(tx, self.inode_goal(inode.ino), u64::from(count))?,
+        };
+
+        let mut runs = Vec::new();
+        let mut logical = 0;
+        for (start, len) in allocated {
+            let from = (u64::from(logical) * block_size) as usize;
+            let to = (from + (len * block_size) as usize).min(content.len());
+            self.write_data(start, &content[from..to])?;
+            push_run(
+                &mut runs,
+                Run {
+                    logical,
+                    physical: start,
+                    len: len as u32,
+                    uninit: false,
+                },
+            );
+            logical += len as u32;
+        }
+        self.set_runs(tx, inode, &runs)?;
+        for run in old {
+            self.free_blocks(tx, run.physical, u64::from(run.len))?;
+        }
+        self.set_size(inode, content.len() as u64);
+        Ok(())
+    }
+
+    fn set_size(&mut self, inode: &mut Inode, size: u64) {
+        inode.set_size(size);
+        if size > MAX_SMALL_FILE {
+            self.sb.set_ro_compat(RO_COMPAT_LARGE_FILE);
+        }
+    }
+
+    /// Free an inode whose last link is gone, with its blocks
+    fn release_inode(&mut self, tx: &mut Tx, inode: &mut Inode, now: u32) -> Result<()> {
+        let (runs, tree) = self.map(tx, inode)?;
+        for run in runs {
+            self.free_blocks(tx, run.physical, u64::from(run.len))?;
+        }
+        for block in tree {
+            self.free_blocks(tx, block, 1)?;
+        }
+        let xattr_block = inode.xattr_block();
+        if xattr_block != 0 {
+            self.release_xattr_block(tx, xattr_block)?;
+        }
+        inode.set_links(0);
+        inode.set_ctime(now);
+        inode.set_dtime(now);
+        self.write_inode(tx, inode)?;
+        self.free_inode(tx, inode.ino, inode.is_dir())
+    }
+
+    /// Drop a reference to an extended attribute block, which inodes with
+    /// the same attributes share
+    fn release_xattr_block(&mut self, tx: &mut Tx, block: u64) -> Result<()> {
+        let data = self.read_block(tx, block)?;
+        if le_u32(&data, 0) != XATTR_MAGIC {
+            return Err(Error::InvalidFormat(format!(
+                "corrupt extended attribute block {}",
+                block
+            )));
+        }
+        let refs = le_u32(&data, 4);
+        if refs <= 1 {
+            return self.free_blocks(tx, block, 1);
+        }
+        let csum = self.sb.metadata_csum();
+        let seed = crc32c(self.sb.csum_seed, &block.to_le_bytes());
+        let data = self.block_mut(tx, block)?;
+        put_u32(data, 4, refs - 1);
+        if csum {
+            put_u32(data, 0x10, 0);
+            let crc = crc32c(seed, data);
+            put_u32(data, 0x10, crc);
+        }
+        Ok(())
+    }
+}
+
+/// Permission bits of `mode`, for an inode mode
+fn permissions(mode: u32) -> u16 {
+    (mode & 0o7777) as u16
+}
+
+/// Blocks holding `size` bytes, if a file can have that many
+fn logical_blocks(size: u64, block_size: u64) -> Result<u32> {
+    u32::try_from(size.div_ceil(block_size))
+        .map_err(|_| Error::ResourceLimit(format!("{} bytes is too large for ext4", size)))
+}
+
+fn now() -> u32 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs() as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::process::Command;
+
+    /// A fresh filesystem made by mke2fs, or `None` where it isn't
+    /// installed
+    fn mkfs(dir: &Path, options: &[&str]) -> Option<std::path::PathBuf> {
+        let path = dir.join("fs.img");
+        File::create(&path)
+            .unwrap()
+            .set_len(64 * 1024 * 1024)
+            .unwrap();
+        let status = Command::new("mkfs.ext4")
+            .args(["-F", "-q"])
+            .args(options)
+            .arg(&path)
+            .status()
+            .ok()?;
+        assert!(status.success());
+        Some(path)
+    }
+
+    /// Whether `e2fsck` finds the filesystem clean, if it is installed
+    fn fsck_clean(path: &Path) -> bool {
+        match Command::new("e2fsck").arg("-fn").arg(path).output() {
+            Ok(output) => output.status.success(),
+            Err(_) => true,
+        }
+    }
+
+    #[test]
+    fn test_write_read_back() {
+        let dir = tempfile::tempdir().unwrap();
+        let Some(path) = mkfs(dir.path(), &[]) else {
+            return;
+        };
+
+        let mut fs = Ext4::open_rw(&path, 0).unwrap();
+        fs.mkdir("/etc", 0o755).unwrap();
+        fs.write_file("/etc/hostname", b"web01\n", 0o644).unwrap();
+        let big: Vec<u8> = (0..300_000u32).map(|i| i as u8).collect();
+        fs.write_file("/etc/big", &big, 0o600).unwrap();
+        fs.write_file("/etc/hostname", b"web02\n", 0o600).unwrap();
+        fs.chmod("/etc/big", 0o640).unwrap();
+        fs.truncate("/etc/big", 5000).unwrap();
+        fs.write_file("/etc/gone", b"x", 0o644).unwrap();
+        fs.unlink("/etc/gone").unwrap();
+        drop(fs);
+        assert!(fsck_clean(&path));
+
+        let fs = Ext4::open(&path, 0).unwrap();
+        assert_eq!(fs.read_file("/etc/hostname").unwrap(), b"web02\n");
+        assert_eq!(fs.stat("/etc/hostname").unwrap().mode, 0o100644);
+        assert_eq!(fs.read_file("/etc/big").unwrap(), &big[..5000]);
+        assert_eq!(fs.stat("/etc/big").unwrap().mode, 0o100640);
+        assert!(!fs.exists("/etc/gone").unwrap());
+        let names: Vec<String> = fs
+            .list_dir("/etc")
+            .unwrap()
+            .into_iter()
+            .map(|entry| entry.name)
+            .collect();
+        assert_eq!(names, [".", "..", "hostname", "big"]);
+        assert_eq!(fs.stat("/").unwrap().links, 4);
+    }
+
+    #[test]
+    fn test_many_entries() {
+        let dir = tempfile::tempdir().unwrap();
+        let Some(path) = mkfs(dir.path(), &["-b", "1024", "-O", "^metadata_csum"]) else {
+            return;
+        };
+
+        let mut fs = Ext4::open_rw(&path, 0).unwrap();
+        fs.mkdir("/d", 0o755).unwrap();
+        for i in 0..200 {
+            fs.write_file(&format!("/d/file-{}", i), b"", 0o644)
+                .unwrap();
+        }
+        for i in (0..200).step_by(3) {
+            fs.unlink(&format!("/d/file-{}", i)).unwrap();
+        }
+        drop(fs);
+        assert!(fsck_clean(&path));
+
+        let fs = Ext4::open(&path, 0).unwrap();
+        assert_eq!(fs.list_dir("/d").unwrap().len(), 2 + 133);
+        assert!(fs.exists("/d/file-199").unwrap());
+        assert!(!fs.exists("/d/file-198").unwrap());
+    }
+
+    #[test]
+    fn test_deleted() {
+        let dir = tempfile::tempdir().unwrap();
+        let Some(path) = mkfs(dir.path(), &[]) else {
+            return;
+        };
+
+        let mut fs = Ext4::open_rw(&path, 0).unwrap();
+        fs.mkdir("/home", 0o755).unwrap();
+        let notes: Vec<u8> = (0..20_000u32).map(|i| (i % 251) as u8).collect();
+        fs.write_file("/home/notes.txt", &notes, 0o600).unwrap();
+        fs.write_file("/home/keep", b"keep", 0o644).unwrap();
+        fs.unlink("/home/notes.txt").unwrap();
+        drop(fs);
+
+        let fs = Ext4::open(&path, 0).unwrap();
+        let deleted = fs.deleted().unwrap();
+        assert_eq!(deleted.len(), 1);
+        let file = &deleted[0];
+        assert_eq!(file.path.as_deref(), Some("/home/notes.txt"));
+        assert_eq!((file.mode, file.size), (0o100600, 20_000));
+        assert!(!file.orphan && file.dtime != 0);
+        assert_eq!(file.blocks_reused, 0);
+        assert_eq!(fs.recover(file).unwrap(), notes);
+    }
+
+    #[test]
+    fn test_deleted_from_journal() {
+        let dir = tempfile::tempdir().unwrap();
+        let Some(path) = mkfs(dir.path(), &[]) else {
+            return;
+        };
+
+        let mut fs = Ext4::open_rw(&path, 0).unwrap();
+        let secret = b"launch codes\n".repeat(1000);
+        fs.write_file("/secret", &secret, 0o600).unwrap();
+        let ino = fs.stat("/secret").unwrap().ino;
+        drop(fs);
+
+        // Delete it the way the kernel does, clearing the block map in
+        // place and leaving the journal as the last transaction left it
+        let fs = Ext4::open(&path, 0).unwrap();
+        let index = u64::from((ino - 1) % fs.sb.inodes_per_group);
+        let at = fs.groups.inode_table((ino - 1) / fs.sb.inodes_per_group) * fs.sb.block_size
+            + index * fs.sb.inode_size as u64;
+        let file = OpenOptions::new().write(true).open(&path).unwrap();
+        file.write_all_at(&[0; 4], at + 0x4).unwrap();
+        file.write_all_at(&1_700_000_000u32.to_le_bytes(), at + 0x14)
+            .unwrap();
+        file.write_all_at(&[0; 2], at + 0x1a).unwrap();
+        file.write_all_at(&[0; 60], at + 0x28).unwrap();
+        drop(file);
+
+        let fs = Ext4::open(&path, 0).unwrap();
+        let deleted = fs.deleted().unwrap();
+        assert_eq!(deleted.len(), 1);
+        assert_eq!(deleted[0].ino, ino);
+        assert!(deleted[0].sequence.is_some());
+        assert_eq!(deleted[0].dtime, 1_700_000_000);
+        assert_eq!(fs.recover(&deleted[0]).unwrap(), secret);
+    }
+
+    #[test]
+    fn test_refuses_read_only_and_unsupported() {
+        let dir = tempfile::tempdir().unwrap();
+        let Some(path) = mkfs(dir.path(), &["-O", "inline_data"]) else {
+            return;
+        };
+        assert!(matches!(
+            Ext4::open_rw(&path, 0),
+            Err(Error::Unsupported(_))
+        ));
+        let mut fs = Ext4::open(&path, 0).unwrap();
+        assert!(matches!(
+            fs.mkdir("/etc", 0o755),
+            Err(Error::PermissionDenied(_))
+        ));
+    }
+}
@@ -0,0 +1,454 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Deleted and orphaned files
+//!
+//! Deleting a file clears the block map in its inode, so the inode table
+//! no longer says where the data was. The journal often still holds an
+//! older copy of the inode's table block, logged while the file existed:
+//! its block map, with the extent tree blocks logged up to then, leads
+//! back to the data as long as nothing has reused the blocks since.
+//! Orphans, files unlinked while still open, keep their blocks until the
+//! next mount processes the orphan list.
+//!
+//! Names come from directory entries a delete leaves behind in the slack
+//! of the entry before them.
+
+use super::alloc::bit;
+use super::dir::{physical, rec_len, FT_DIR, ROOT_INO};
+use super::inode::{Inode, Run, EXTENTS_FL, EXTENT_MAGIC, INDEX_FL};
+use super::jbd2::{logged_blocks, Logged};
+use super::layout::{le_u16, le_u32, BG_BLOCK_UNINIT, BG_INODE_UNINIT, COMPAT_HAS_JOURNAL};
+use super::{Ext4, Ext4Deleted, Tx};
+use crate::core::Result;
+use std::collections::hash_map::Entry;
+use std::collections::{BTreeMap, BTreeSet, HashMap, VecDeque};
+
+/// Superblock fields of the orphan list and orphan file
+const LAST_ORPHAN: usize = 0xe8;
+const ORPHAN_FILE_INUM: usize = 0x26c;
+const COMPAT_ORPHAN_FILE: u32 = 0x1000;
+/// Orphan file blocks end in a magic and a checksum
+const ORPHAN_BLOCK_TAIL: usize = 8;
+const ORPHAN_BLOCK_MAGIC: u32 = 0x0b10_ca04;
+
+/// Entries left in `block[start..end]`, the slack after a live entry
+/// where a delete merged the entries following it
+fn hidden_entries(
+    block: &[u8],
+    mut start: usize,
+    end: usize,
+    inodes_count: u32,
+) -> Vec<(u32, Vec<u8>)> {
+    let mut found = Vec::new();
+    start = (start + 3) & !3;
+    while start + 8 <= end {
+        let inode = le_u32(block, start);
+        let len = usize::from(le_u16(block, start + 4));
+        let name_len = usize::from(block[start + 6]);
+        let name = &block[(start + 8).min(end)..(start + 8 + name_len).min(end)];
+        let valid = inode != 0
+            && inode <= inodes_count
+            && name_len > 0
+            && name.len() == name_len
+            && len >= rec_len(name_len)
+            && len % 4 == 0
+            && !name.iter().any(|&b| b == 0 || b == b'/')
+            && name != b"."
+            && name != b"..";
+        if valid {
+            found.push((inode, name.to_vec()));
+            start += rec_len(name_len);
+        } else {
+            start += 4;
+        }
+    }
+    found
+}
+
+impl Ext4 {
+    /// Deleted and orphaned regular files whose data may still be
+    /// recovered with [`Ext4::recover`]
+    pub fn deleted(&self) -> Result<Vec<Ext4Deleted>> {
+        let tx = Tx::default();
+        let mut found = BTreeMap::new();
+
+        for ino in self.orphans(&tx)? {
+            let inode = self.read_inode(&tx, ino)?;
+            if inode.is_reg() && inode.links() == 0 {
+                let file = self.describe(inode, true, None, BTreeMap::new());
+                found.insert(ino, file);
+            }
+        }
+
+        // Freed inodes keep their mode, generation and deletion time
+        let mut dead = BTreeMap::new();
+        for group in 0..self.sb.group_count {
+            if self.sb.uninit_groups() && self.groups.flags(group) & BG_INODE_UNINIT != 0 {
+                continue;
+            }
+            let mut used = u64::from(self.sb.inodes_per_group);
+            if self.sb.uninit_groups() {
+                used = used.saturating_sub(self.groups.itable_unused(group));
+            }
+            let table = self.groups.inode_table(group);
+            let per_block = self.sb.block_size / self.sb.inode_size as u64;
+            for block in 0..used.div_ceil(per_block) {
+                let data = self.read_disk_block(table + block)?;
+                for (i, raw) in data.chunks_exact(self.sb.inode_size).enumerate() {
+                    let index = block * per_block + i as u64;
+                    let ino = group * self.sb.inodes_per_group + index as u32 + 1;
+                    let inode = Inode {
+                        ino,
+                        raw: raw.to_vec(),
+                    };
+                    if index >= used
+                        || !inode.is_reg()
+                        || inode.links() != 0
+                        || inode.dtime() == 0
+                        || found.contains_key(&ino)
+                    {
+                        continue;
+                    }
+                    // ext2 and some tools leave the block map in place
+                    let file = self.describe(inode.clone(), false, None, BTreeMap::new());
+                    if file.blocks > 0 && file.size > 0 {
+                        found.insert(ino, file);
+                    } else {
+                        dead.insert(ino, inode);
+                    }
+                }
+            }
+        }
+
+        if !dead.is_empty() {
+            for (ino, file) in self.journal_copies(&dead)? {
+                found.insert(ino, file);
+            }
+        }
+
+        let wanted: BTreeSet<u32> = found.keys().copied().collect();
+        let names = self.deleted_names(&tx, &wanted);
+        let mut bitmaps = HashMap::new();
+        let mut files: Vec<Ext4Deleted> = found.into_values().collect();
+        for file in &mut files {
+            file.path = names.get(&file.ino).cloned();
+            if !file.orphan {
+                file.blocks_reused = self.count_used(&file.runs, &mut bitmaps)?;
+            }
+        }
+        Ok(files)
+    }
+
+    /// What is left of a file [`Ext4::deleted`] found; blocks reused since
+    /// read as their new contents
+    pub fn recover(&self, file: &Ext4Deleted) -> Result<Vec<u8>> {
+        let tx = Tx {
+            blocks: file.tree.clone(),
+            ..Tx::default()
+        };
+        let inode = Inode {
+            ino: file.ino,
+            raw: file.raw.clone(),
+        };
+        self.read_data(&tx, &inode)
+    }
+
+    fn describe(
+        &self,
+        inode: Inode,
+        orphan: bool,
+        sequence: Option<u32>,
+        tree: BTreeMap<u64, Vec<u8>>,
+    ) -> Ext4Deleted {
+        let tx = Tx {
+            blocks: tree,
+            ..Tx::default()
+        };
+        let runs = self
+            .map(&tx, &inode)
+            .map(|(runs, _)| runs)
+            .unwrap_or_default();
+        Ext4Deleted {
+            ino: inode.ino,
+            path: None,
+            mode: u32::from(inode.mode()),
+            uid: inode.uid(),
+            gid: inode.gid(),
+            size: inode.size(),
+            mtime: i64::from(inode.mtime()),
+            // The orphan list links orphans through this field
+            dtime: if orphan { 0 } else { i64::from(inode.dtime()) },
+            orphan,
+            sequence,
+            blocks: runs.iter().map(|run| u64::from(run.len)).sum(),
+            blocks_reused: 0,
+            runs,
+            raw: inode.raw,
+            tree: tx.blocks,
+        }
+    }
+
+    /// Inodes on the orphan list, and in the orphan file
+    fn orphans(&self, tx: &Tx) -> Result<Vec<u32>> {
+        let mut orphans = Vec::new();
+        let mut next = le_u32(&self.sb.raw, LAST_ORPHAN);
+        while next != 0 && !orphans.contains(&next) && orphans.len() < self.sb.inodes_count as usize
+        {
+            let Ok(inode) = self.read_inode(tx, next) else {
+                break;
+            };
+            orphans.push(next);
+            next = inode.dtime();
+        }
+
+        let orphan_file = le_u32(&self.sb.raw, ORPHAN_FILE_INUM);
+        if self.sb.compat() & COMPAT_ORPHAN_FILE != 0 && orphan_file != 0 {
+            let inode = self.read_inode(tx, orphan_file)?;
+            let data = self.read_data(tx, &inode)?;
+            for block in data.chunks_exact(self.sb.block_size as usize) {
+                let entries = block.len() - ORPHAN_BLOCK_TAIL;
+                if le_u32(block, entries) != ORPHAN_BLOCK_MAGIC {
+                    continue;
+                }
+                for entry in block[..entries].chunks_exact(4) {
+                    let ino = le_u32(entry, 0);
+                    if ino != 0 && !orphans.contains(&ino) {
+                        orphans.push(ino);
+                    }
+                }
+            }
+        }
+        Ok(orphans)
+    }
+
+    /// Deleted inodes of `dead` as the newest journal copy of their table
+    /// block still mapping their data has them
+    fn journal_copies(&self, dead: &BTreeMap<u32, Inode>) -> Result<Vec<(u32, Ext4Deleted)>> {
+        let tx = Tx::default();
+        let journal_ino = self.sb.journal_inode();
+        if self.sb.compat() & COMPAT_HAS_JOURNAL == 0 || journal_ino == 0 {
+            return Ok(Vec::new());
+        }
+        let journal = self.read_inode(&tx, journal_ino)?;
+        let (runs, _) = self.map(&tx, &journal)?;
+        let read = |index: u32| match physical(&runs, index) {
+            Some(block) => self.read_disk_block(block),
+            None => Ok(vec![0u8; self.sb.block_size as usize]),
+        };
+        let superblock = read(0)?;
+        let mut logged = logged_blocks(&superblock, &read)?;
+        logged.sort_by_key(|copy| std::cmp::Reverse(copy.sequence));
+        let read_copy = |copy: &Logged| -> Result<Vec<u8>> {
+            let mut data = read(copy.index)?;
+            copy.unescape(&mut data);
+            Ok(data)
+        };
+
+        let per_block = self.sb.block_size / self.sb.inode_size as u64;
+        let table_blocks = u64::from(self.sb.inodes_per_group).div_ceil(per_block);
+        let tables: BTreeMap<u64, u32> = (0..self.sb.group_count)
+            .map(|group| (self.groups.inode_table(group), group))
+            .collect();
+        let table_of = |block: u64| {
+            tables
+                .range(..=block)
+                .next_back()
+                .filter(|(start, _)| block < *start + table_blocks)
+                .map(|(start, group)| (block - start, *group))
+        };
+
+        // Copies of extent tree blocks, read once a deep tree needs them
+        let mut trees: Option<Vec<(Logged, Vec<u8>)>> = None;
+        let mut recovered = Vec::new();
+        let mut done = BTreeSet::new();
+        for copy in &logged {
+            let Some((block, group)) = table_of(copy.block) else {
+                continue;
+            };
+            let first = group * self.sb.inodes_per_group + (block * per_block) as u32 + 1;
+            if !(first..first + per_block as u32).any(|ino| dead.contains_key(&ino)) {
+                continue;
+            }
+            let data = read_copy(copy)?;
+            for (i, raw) in data.chunks_exact(self.sb.inode_size).enumerate() {
+                let ino = first + i as u32;
+                let Some(now) = dead.get(&ino) else {
+                    continue;
+                };
+                let inode = Inode {
+                    ino,
+                    raw: raw.to_vec(),
+                };
+                if done.contains(&ino)
+                    || inode.generation() != now.generation()
+                    || inode.mode() != now.mode()
+                    || inode.links() == 0
+                    || inode.size() == 0
+                {
+                    continue;
+                }
+
+                let deep = inode.flags() & EXTENTS_FL != 0 && le_u16(inode.i_block(), 0x6) > 0;
+                let mut tree = BTreeMap::new();
+                if deep {
+                    if trees.is_none() {
+                        let mut copies = Vec::new();
+                        for other in &logged {
+                            if table_of(other.block).is_some() {
+                                continue;
+                            }
+                            let data = read_copy(other)?;
+                            if le_u16(&data, 0) == EXTENT_MAGIC {
+                                copies.push((*other, data));
+                            }
+                        }
+                        trees = Some(copies);
+                    }
+                    // The tree as of the transaction that logged the inode
+                    for (other, data) in trees.iter().flatten() {
+                        if other.sequence <= copy.sequence {
+                            tree.entry(other.block).or_insert_with(|| data.clone());
+                        }
+                    }
+                }
+
+                let tx = Tx {
+                    blocks: tree,
+                    ..Tx::default()
+                };
+                match self.map(&tx, &inode) {
+                    Ok((runs, _)) if !runs.is_empty() => {}
+                    _ => continue,
+                }
+                done.insert(ino);
+                let mut file = self.describe(inode, false, Some(copy.sequence), tx.blocks);
+                file.dtime = i64::from(now.dtime());
+                recovered.push((ino, file));
+            }
+        }
+        Ok(recovered)
+    }
+
+    /// Paths of deleted entries naming one of `wanted`, from every
+    /// directory reachable from the root
+    fn deleted_names(&self, tx: &Tx, wanted: &BTreeSet<u32>) -> BTreeMap<u32, String> {
+        let mut names = BTreeMap::new();
+        let mut seen = BTreeSet::from([ROOT_INO]);
+        let mut queue = VecDeque::from([(ROOT_INO, String::new())]);
+        while let Some((ino, path)) = queue.pop_front() {
+            let Ok(dir) = self.read_inode(tx, ino) else {
+                continue;
+            };
+            if !dir.is_dir() {
+                continue;
+            }
+            let indexed = dir.flags() & INDEX_FL != 0;
+            let Ok(runs) = self.dir_blocks(tx, &dir) else {
+                continue;
+            };
+            for run in runs {
+                for i in 0..run.len {
+                    let Ok(block) = self.read_block(tx, run.physical + u64::from(i)) else {
+                        continue;
+                    };
+                    let Ok(entries) = self.parse_entries(&block) else {
+                        continue;
+                    };
+                    // Index blocks of a hashed directory: its first block, and
+                    // those of one empty entry spanning the block
+                    let index = indexed
+                        && (run.logical + i == 0
+                            || entries
+                                .first()
+                                .is_some_and(|e| e.inode == 0 && e.rec_len == block.len()));
+                    for entry in &entries {
+                        let is_dir = match entry.file_type {
+                            0 => self.read_inode(tx, entry.inode).is_ok_and(|i| i.is_dir()),
+                            file_type => file_type == FT_DIR,
+                        };
+                        if entry.inode != 0
+                            && entry.name != b"."
+                            && entry.name != b".."
+                            && is_dir
+                            && seen.insert(entry.inode)
+                        {
+                            let child =
+                                format!("{}/{}", path, String::from_utf8_lossy(&entry.name));
+                            queue.push_back((entry.inode, child));
+                        }
+                        if index {
+                            continue;
+                        }
+                        let start = entry.offset + rec_len(entry.name.len());
+                        let end = entry.offset + entry.rec_len;
+                        for (ino, name) in hidden_entries(&block, start, end, self.sb.inodes_count)
+                        {
+                            if wanted.contains(&ino) {
+                                names.entry(ino).or_insert_with(|| {
+                                    format!("{}/{}", path, String::from_utf8_lossy(&name))
+                                });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+        names
+    }
+
+    /// How many blocks of `runs` are in use again
+    fn count_used(&self, runs: &[Run], bitmaps: &mut HashMap<u32, Option<Vec<u8>>>) -> Result<u64> {
+        let mut used = 0;
+        for run in runs.iter().filter(|run| !run.uninit) {
+            for block in run.physical..run.physical + u64::from(run.len) {
+                let group = self.block_group(block);
+                let bitmap = match bitmaps.entry(group) {
+                    Entry::Occupied(entry) => entry.into_mut(),
+                    Entry::Vacant(entry) => entry.insert(
+                        if self.sb.uninit_groups()
+                            && self.groups.flags(group) & BG_BLOCK_UNINIT != 0
+                        {
+                            None
+                        } else {
+                            Some(self.read_disk_block(self.groups.block_bitmap(group))?)
+                        },
+                    ),
+                };
+                if let Some(bitmap) = bitmap {
+                    if bit(bitmap, block - self.group_start(group)) {
+                        used += 1;
+                    }
+                }
+            }
+        }
+        Ok(used)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(block: &mut [u8], offset: usize, inode: u32, rec_len: u16, name: &[u8]) {
+        block[offset..offset + 4].copy_from_slice(&inode.to_le_bytes());
+        block[offset + 4..offset + 6].copy_from_slice(&rec_len.to_le_bytes());
+        block[offset + 6] = name.len() as u8;
+        block[offset + 7] = 1;
+        block[offset + 8..offset + 8 + name.len()].copy_from_slice(name);
+    }
+
+    #[test]
+    fn test_hidden_entries() {
+        // "a" took over the space of "secret.txt" when it was deleted
+        let mut block = vec![0u8; 64];
+        entry(&mut block, 0, 12, 64, b"a");
+        entry(&mut block, 12, 13, 52, b"secret.txt");
+        let live = rec_len(1);
+        assert_eq!(
+            hidden_entries(&block, live, 64, 100),
+            vec![(13, b"secret.txt".to_vec())]
+        );
+        // Inodes past the end of the table are noise, not entries
+        assert!(hidden_entries(&block, live, 64, 12).is_empty());
+        assert!(hidden_entries(&[0u8; 64], 0, 64, 100).is_empty());
+    }
+}
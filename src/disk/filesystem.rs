@@ -32,6 +32,10 @@ pub enum FileSystemType {
     Apfs,
     /// ISO9660 (CD/DVD)
     Iso9660,
+    /// SquashFS (live media, appliances)
+    Squashfs,
+    /// EROFS (live media, image based OSes)
+    Erofs,
     /// Linux Swap
     Swap,
     /// Unknown filesystem
@@ -64,6 +68,8 @@ impl FileSystem {
             Self::detect_hfsplus,
             Self::detect_apfs,
             Self::detect_iso9660,
+            Self::detect_squashfs,
+            Self::detect_erofs,
             Self::detect_swap,
         ];
 
@@ -161,10 +167,27 @@ impl FileSystem {
 
         // Check XFS magic "XFSB"
         if &superblock[0..4] == b"XFSB" {
+            // Label at offset 108 (12 bytes), same in v4 and v5 superblocks
+            let label = String::from_utf8_lossy(&superblock[108..120])
+                .trim_end_matches('\0')
+                .to_string();
+            let label = if label.is_empty() { None } else { Some(label) };
+
+            // UUID at offset 32 (16 bytes)
+            let uuid = &superblock[32..48];
+            let uuid = format!(
+                "{}-{}-{}-{}-{}",
+                hex(&uuid[0..4]),
+                hex(&uuid[4..6]),
+                hex(&uuid[6..8]),
+                hex(&uuid[8..10]),
+                hex(&uuid[10..16])
+            );
+
             return Ok(Self {
                 fs_type: FileSystemType::Xfs,
-                label: None,
-                uuid: None,
+                label,
+                uuid: Some(uuid),
             });
         }
 
@@ -175,15 +198,32 @@ impl FileSystem {
     fn detect_btrfs(reader: &mut DiskReader, partition_offset: u64) -> Result<Self> {
         // Btrfs superblock is at offset 65536
         let superblock_offset = partition_offset + 65536;
-        let mut superblock = vec![0u8; 512];
+        let mut superblock = vec![0u8; 1024];
         reader.read_exact_at(superblock_offset, &mut superblock)?;
 
         // Check Btrfs magic "_BHRfS_M"
         if &superblock[64..72] == b"_BHRfS_M" {
+            // Label at offset 299 (256 bytes)
+            let label = String::from_utf8_lossy(&superblock[299..555])
+                .trim_end_matches('\0')
+                .to_string();
+            let label = if label.is_empty() { None } else { Some(label) };
+
+            // Filesystem UUID (fsid) at offset 32 (16 bytes), as used by UUID= in fstab
+            let fsid = &superblock[32..48];
+            let uuid = format!(
+                "{}-{}-{}-{}-{}",
+                hex(&fsid[0..4]),
+                hex(&fsid[4..6]),
+                hex(&fsid[6..8]),
+                hex(&fsid[8..10]),
+                hex(&fsid[10..16])
+            );
+
             return Ok(Self {
                 fs_type: FileSystemType::Btrfs,
-                label: None,
-                uuid: None,
+                label,
+                uuid: Some(uuid),
             });
         }
 
@@ -341,6 +381,58 @@ impl FileSystem {
         Err(Error::Detection("Not an ISO9660 filesystem".to_string()))
     }
 
+    /// Detect SquashFS filesystem
+    fn detect_squashfs(reader: &mut DiskReader, partition_offset: u64) -> Result<Self> {
+        let mut superblock = vec![0u8; 4];
+        reader.read_exact_at(partition_offset, &mut superblock)?;
+
+        // Check SquashFS magic "hsqs" (little-endian 0x73717368)
+        if &superblock[0..4] == b"hsqs" {
+            return Ok(Self {
+                fs_type: FileSystemType::Squashfs,
+                label: None,
+                uuid: None,
+            });
+        }
+
+        Err(Error::Detection("Not a SquashFS filesystem".to_string()))
+    }
+
+    /// Detect EROFS filesystem
+    fn detect_erofs(reader: &mut DiskReader, partition_offset: u64) -> Result<Self> {
+        // EROFS superblock is at offset 1024
+        let mut superblock = vec![0u8; 128];
+        reader.read_exact_at(partition_offset + 1024, &mut superblock)?;
+
+        // Check EROFS magic 0xE0F5E1E2 (little-endian)
+        if superblock[0..4] == 0xe0f5_e1e2u32.to_le_bytes() {
+            // Volume name at offset 64 (16 bytes)
+            let label = String::from_utf8_lossy(&superblock[64..80])
+                .trim_end_matches('\0')
+                .to_string();
+            let label = if label.is_empty() { None } else { Some(label) };
+
+            // UUID at offset 48 (16 bytes)
+            let uuid = &superblock[48..64];
+            let uuid = format!(
+                "{}-{}-{}-{}-{}",
+                hex(&uuid[0..4]),
+                hex(&uuid[4..6]),
+                hex(&uuid[6..8]),
+                hex(&uuid[8..10]),
+                hex(&uuid[10..16])
+            );
+
+            return Ok(Self {
+                fs_type: FileSystemType::Erofs,
+                label,
+                uuid: Some(uuid),
+            });
+        }
+
+        Err(Error::Detection("Not an EROFS filesystem".to_string()))
+    }
+
     /// Detect Linux Swap
     fn detect_swap(reader: &mut DiskReader, partition_offset: u64) -> Result<Self> {
         // Swap signature is at the end of the first page (4096 bytes)
@@ -407,6 +499,11 @@ impl FileSystem {
     }
 }
 
+/// Lowercase hex of `bytes`
+fn hex(bytes: &[u8]) -> String {
+    bytes.iter().map(|b| format!("{:02x}", b)).collect()
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -416,4 +513,38 @@ mod tests {
         assert_eq!(FileSystemType::Ext, FileSystemType::Ext);
         assert_eq!(FileSystemType::Ntfs, FileSystemType::Ntfs);
     }
+
+    #[test]
+    fn test_detect_live_images() {
+        use std::os::unix::fs::FileExt;
+
+        let dir = tempfile::tempdir().unwrap();
+        let partition = Partition {
+            number: 1,
+            start_lba: 0,
+            size_sectors: 512,
+            type_id: 0x83,
+            bootable: false,
+            type_guid: None,
+        };
+        let detect = |name: &str, offset: u64, data: &[u8]| {
+            let path = dir.path().join(name);
+            let file = std::fs::File::create(&path).unwrap();
+            file.set_len(256 * 1024).unwrap();
+            file.write_all_at(data, offset).unwrap();
+            FileSystem::detect(&mut DiskReader::open(&path).unwrap(), &partition).unwrap()
+        };
+
+        let fs = detect("squashfs.img", 0, b"hsqs");
+        assert_eq!(fs.fs_type(), &FileSystemType::Squashfs);
+
+        let mut superblock = vec![0u8; 128];
+        superblock[0..4].copy_from_slice(&0xe0f5_e1e2u32.to_le_bytes());
+        superblock[48..64].copy_from_slice(&[0x11; 16]);
+        superblock[64..70].copy_from_slice(b"rootfs");
+        let fs = detect("erofs.img", 1024, &superblock);
+        assert_eq!(fs.fs_type(), &FileSystemType::Erofs);
+        assert_eq!(fs.label(), Some("rootfs"));
+        assert_eq!(fs.uuid(), Some("11111111-1111-1111-1111-111111111111"));
+    }
 }
@@ -0,0 +1,217 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! B-tree files: the catalog, extents overflow and attributes files
+//!
+//! Nodes start with a descriptor and end with a table of the offsets of
+//! their records. Index records point at the node whose first key they
+//! hold; leaf nodes are linked in key order.
+
+use super::{be_u16, be_u32, Extent, HfsPlus};
+use crate::core::{Error, Result};
+use std::cmp::Ordering;
+
+const NODE_DESCRIPTOR_SIZE: usize = 14;
+const KIND_LEAF: i8 = -1;
+const KIND_INDEX: i8 = 0;
+const KIND_HEADER: i8 = 1;
+
+/// Index keys are as long as they need to be, not `max_key_len`
+const VARIABLE_INDEX_KEYS: u32 = 0x4;
+/// Catalog keys compared as binary (HFSX) rather than case-folded
+const KEY_COMPARE_BINARY: u8 = 0xbc;
+const MIN_NODE_SIZE: usize = 512;
+const MAX_NODE_SIZE: usize = 32768;
+/// Deeper trees than this are corrupt
+const MAX_DEPTH: usize = 16;
+
+/// A B-tree file; the default one is empty
+#[derive(Default)]
+pub(super) struct BTree {
+    /// Where the B-tree file is
+    extents: Vec<Extent>,
+    node_size: usize,
+    root: u32,
+    total_nodes: u32,
+    max_key_len: usize,
+    variable_index_keys: bool,
+    binary_keys: bool,
+}
+
+impl BTree {
+    pub fn binary_keys(&self) -> bool {
+        self.binary_keys
+    }
+}
+
+struct Node {
+    raw: Vec<u8>,
+    kind: i8,
+    next: u32,
+    /// Record offsets, with the offset of free space last
+    offsets: Vec<usize>,
+}
+
+impl Node {
+    fn parse(raw: Vec<u8>, number: u32) -> Result<Self> {
+        let corrupt = || Error::InvalidFormat(format!("HFS+ B-tree node {} is corrupt", number));
+        let count = usize::from(be_u16(&raw, 10));
+        let table = raw
+            .len()
+            .checked_sub(2 * (count + 1))
+            .filter(|&table| table >= NODE_DESCRIPTOR_SIZE)
+            .ok_or_else(corrupt)?;
+        let offsets: Vec<usize> = (0..=count)
+            .map(|i| usize::from(be_u16(&raw, raw.len() - 2 * (i + 1))))
+            .collect();
+        if offsets[0] < NODE_DESCRIPTOR_SIZE
+            || offsets.windows(2).any(|pair| pair[0] > pair[1])
+            || offsets[count] > table
+        {
+            return Err(corrupt());
+        }
+        Ok(Self {
+            kind: raw[8] as i8,
+            next: be_u32(&raw, 0),
+            offsets,
+            raw,
+        })
+    }
+
+    fn count(&self) -> usize {
+        self.offsets.len() - 1
+    }
+
+    /// Key and what follows it of record `i`
+    fn record(&self, i: usize) -> Result<(&[u8], &[u8])> {
+        let record = &self.raw[self.offsets[i]..self.offsets[i + 1]];
+        if record.len() < 2 {
+            return Err(Error::InvalidFormat(
+                "corrupt HFS+ B-tree record".to_string(),
+            ));
+        }
+        let key_end = 2 + usize::from(be_u16(record, 0));
+        if key_end > record.len() {
+            return Err(Error::InvalidFormat(
+                "corrupt HFS+ B-tree record".to_string(),
+            ));
+        }
+        // Data starts on an even offset
+        let data = (key_end + 1) & !1;
+        Ok((&record[..key_end], record.get(data..).unwrap_or(&[])))
+    }
+}
+
+impl HfsPlus {
+    /// The B-tree stored in `extents`
+    pub(super) fn open_btree(&self, extents: Vec<Extent>, name: &str) -> Result<BTree> {
+        let mut header = vec![0u8; MIN_NODE_SIZE];
+        self.read_extents(&extents, 0, &mut header)?;
+        if header[8] as i8 != KIND_HEADER {
+            return Err(Error::InvalidFormat(format!(
+                "HFS+ {} file: no B-tree header",
+                name
+            )));
+        }
+        let node_size = usize::from(be_u16(&header, 32));
+        if !node_size.is_power_of_two() || !(MIN_NODE_SIZE..=MAX_NODE_SIZE).contains(&node_size) {
+            return Err(Error::InvalidFormat(format!(
+                "HFS+ {} file: B-tree node size {}",
+                name, node_size
+            )));
+        }
+        Ok(BTree {
+            extents,
+            node_size,
+            root: be_u32(&header, 16),
+            total_nodes: be_u32(&header, 36),
+            max_key_len: usize::from(be_u16(&header, 34)),
+            variable_index_keys: be_u32(&header, 52) & VARIABLE_INDEX_KEYS != 0,
+            binary_keys: header[51] == KEY_COMPARE_BINARY,
+        })
+    }
+
+    fn read_node(&self, tree: &BTree, number: u32) -> Result<Node> {
+        if number >= tree.total_nodes {
+            return Err(Error::InvalidFormat(format!(
+                "HFS+ B-tree node {} past the end of the tree",
+                number
+            )));
+        }
+        let mut raw = vec![0u8; tree.node_size];
+        self.read_extents(
+            &tree.extents,
+            u64::from(number) * tree.node_size as u64,
+            &mut raw,
+        )?;
+        Node::parse(raw, number)
+    }
+
+    /// Leaf records of `tree` whose keys `range` finds `Equal`, in key
+    /// order; `range` orders keys against a contiguous range of them
+    pub(super) fn scan(
+        &self,
+        tree: &BTree,
+        range: &dyn Fn(&[u8]) -> Ordering,
+    ) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        let mut out = Vec::new();
+        if tree.root == 0 {
+            return Ok(out);
+        }
+
+        // Descend to the last child whose first key is before the range
+        let mut node = self.read_node(tree, tree.root)?;
+        let mut depth = 0;
+        while node.kind == KIND_INDEX {
+            depth += 1;
+            if depth > MAX_DEPTH {
+                return Err(Error::InvalidFormat(
+                    "corrupt HFS+ B-tree: too deep".to_string(),
+                ));
+            }
+            let mut child = None;
+            for i in 0..node.count() {
+                let record = &node.raw[node.offsets[i]..node.offsets[i + 1]];
+                let (key, _) = node.record(i)?;
+                if i > 0 && range(key) != Ordering::Less {
+                    break;
+                }
+                let pointer = if tree.variable_index_keys {
+                    key.len()
+                } else {
+                    2 + tree.max_key_len
+                };
+                if record.len() < pointer + 4 {
+                    return Err(Error::InvalidFormat(
+                        "corrupt HFS+ B-tree index record".to_string(),
+                    ));
+                }
+                child = Some(be_u32(record, pointer));
+            }
+            let child = child
+                .ok_or_else(|| Error::InvalidFormat("empty HFS+ B-tree index node".to_string()))?;
+            node = self.read_node(tree, child)?;
+        }
+
+        // Then walk the leaves until past the range
+        let mut visited = 0;
+        loop {
+            if node.kind != KIND_LEAF {
+                return Err(Error::InvalidFormat(
+                    "corrupt HFS+ B-tree: expected a leaf node".to_string(),
+                ));
+            }
+            for i in 0..node.count() {
+                let (key, data) = node.record(i)?;
+                match range(key) {
+                    Ordering::Less => {}
+                    Ordering::Equal => out.push((key.to_vec(), data.to_vec())),
+                    Ordering::Greater => return Ok(out),
+                }
+            }
+            visited += 1;
+            if node.next == 0 || visited > tree.total_nodes {
+                return Ok(out);
+            }
+            node = self.read_node(tree, node.next)?;
+        }
+    }
+}
@@ -0,0 +1,353 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! The catalog: folders and files keyed by their parent and name, and
+//! extended attributes
+//!
+//! Names are UTF-16 on disk, with ":" where POSIX names have "/". Hard
+//! links are files pointing at an inode file in a hidden directory.
+
+use super::{be_u16, be_u32, Fork, HfsPlus};
+use crate::core::{Error, Result};
+use crate::disk::decmpfs;
+
+pub(super) const ROOT_FOLDER_ID: u32 = 2;
+
+const RECORD_FOLDER: u16 = 1;
+const RECORD_FILE: u16 = 2;
+const RECORD_FOLDER_THREAD: u16 = 3;
+const RECORD_FILE_THREAD: u16 = 4;
+
+/// Seconds from 1904, when HFS dates start, to 1970
+const MAC_EPOCH_OFFSET: i64 = 2_082_844_800;
+
+/// Type and creator of hard links to files, and to directories (Time
+/// Machine)
+const HARDLINK_FILE: &[u8; 8] = b"hlnkhfs+";
+const HARDLINK_DIR: &[u8; 8] = b"fdrpMACS";
+const PRIVATE_FILES: &str = "\0\0\0\0HFS+ Private Data";
+const PRIVATE_DIRS: &str = ".HFS+ Private Directory Data\r";
+
+/// Attribute records: data in the record, or in a fork of its own
+const ATTR_INLINE_DATA: u32 = 0x10;
+const ATTR_FORK_DATA: u32 = 0x20;
+
+const S_IFMT: u16 = 0o170000;
+const S_IFLNK: u16 = 0o120000;
+const S_IFREG: u16 = 0o100000;
+const S_IFDIR: u16 = 0o040000;
+
+const MAX_SYMLINKS: usize = 40;
+const MAX_LINK_LEN: u64 = 4096;
+
+const DT_DIR: u8 = 4;
+const DT_REG: u8 = 8;
+const DT_LNK: u8 = 10;
+
+/// A folder or file record, with the name and parent from its key
+pub(super) struct Entry {
+    pub id: u32,
+    pub parent: u32,
+    pub name: String,
+    pub folder: bool,
+    pub mode: u16,
+    pub uid: u32,
+    pub gid: u32,
+    pub owner_flags: u8,
+    /// Link count of inode files, the inode of hard links
+    special: u32,
+    /// Hard links to the file, set once reached through one
+    pub links: u32,
+    pub mtime: i64,
+    file_info: [u8; 8],
+    pub data: Fork,
+    pub resource: Fork,
+}
+
+impl Entry {
+    fn parse(key: &[u8], record: &[u8]) -> Result<Option<Self>> {
+        let corrupt = || Error::InvalidFormat("corrupt HFS+ catalog record".to_string());
+        let folder = match record.get(..2).map(|kind| be_u16(kind, 0)) {
+            Some(RECORD_FOLDER) => true,
+            Some(RECORD_FILE) => false,
+            Some(_) => return Ok(None),
+            None => return Err(corrupt()),
+        };
+        if key.len() < 6 || record.len() < if folder { 88 } else { 248 } {
+            return Err(corrupt());
+        }
+        let mut mode = be_u16(record, 42);
+        // Files written by classic Mac OS have no permissions
+        if mode & S_IFMT == 0 {
+            mode = if folder {
+                S_IFDIR | 0o755
+            } else {
+                S_IFREG | 0o644
+            };
+        }
+        Ok(Some(Self {
+            id: be_u32(record, 8),
+            parent: be_u32(key, 2),
+            name: key.get(6..).and_then(unistr).ok_or_else(corrupt)?,
+            folder,
+            mode,
+            uid: be_u32(record, 32),
+            gid: be_u32(record, 36),
+            owner_flags: record[41],
+            special: be_u32(record, 44),
+            links: 1,
+            mtime: i64::from(be_u32(record, 16)) - MAC_EPOCH_OFFSET,
+            file_info: record[48..56].try_into().unwrap(),
+            data: if folder {
+                Fork::default()
+            } else {
+                Fork::parse(&record[88..168])
+            },
+            resource: if folder {
+                Fork::default()
+            } else {
+                Fork::parse(&record[168..248])
+            },
+        }))
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.mode & S_IFMT == S_IFDIR
+    }
+
+    pub fn is_reg(&self) -> bool {
+        self.mode & S_IFMT == S_IFREG
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.mode & S_IFMT == S_IFLNK
+    }
+
+    pub fn file_type(&self) -> u8 {
+        if self.is_dir() {
+            DT_DIR
+        } else if self.is_symlink() {
+            DT_LNK
+        } else {
+            DT_REG
+        }
+    }
+
+    /// The hidden directory and name of the inode a hard link points at
+    fn link_inode(&self) -> Option<(&'static str, String)> {
+        if self.folder {
+            None
+        } else if &self.file_info == HARDLINK_FILE {
+            Some((PRIVATE_FILES, format!("iNode{}", self.special)))
+        } else if &self.file_info == HARDLINK_DIR {
+            Some((PRIVATE_DIRS, format!("dir_{}", self.special)))
+        } else {
+            None
+        }
+    }
+}
+
+/// POSIX name from a name on disk: its length, then UTF-16 big-endian
+fn unistr(raw: &[u8]) -> Option<String> {
+    let len = usize::from(be_u16(raw.get(..2)?, 0));
+    let units: Vec<u16> = raw
+        .get(2..2 + 2 * len)?
+        .chunks_exact(2)
+        .map(|unit| u16::from_be_bytes([unit[0], unit[1]]))
+        .collect();
+    Some(String::from_utf16_lossy(&units).replace('/', ":"))
+}
+
+impl HfsPlus {
+    /// The thread record of `id` and the folders and files in it
+    fn catalog_records(&self, id: u32) -> Result<Vec<(Vec<u8>, Vec<u8>)>> {
+        self.scan(&self.catalog, &|key: &[u8]| {
+            if key.len() < 6 {
+                return std::cmp::Ordering::Less;
+            }
+            be_u32(key, 2).cmp(&id)
+        })
+    }
+
+    /// Folders and files in folder `id`
+    pub(super) fn children(&self, id: u32) -> Result<Vec<Entry>> {
+        let mut entries = Vec::new();
+        for (key, record) in self.catalog_records(id)? {
+            if let Some(entry) = Entry::parse(&key, &record)? {
+                entries.push(entry);
+            }
+        }
+        Ok(entries)
+    }
+
+    /// Folder or file `id`, found through its thread record
+    pub(super) fn entry(&self, id: u32) -> Result<Entry> {
+        let thread = self
+            .catalog_records(id)?
+            .into_iter()
+            .find(|(_, record)| {
+                record.len() >= 8
+                    && matches!(be_u16(record, 0), RECORD_FOLDER_THREAD | RECORD_FILE_THREAD)
+            })
+            .ok_or_else(|| Error::NotFound(format!("HFS+ catalog node {}", id)))?;
+        // The thread holds the parent and name the record is keyed by
+        let record = &thread.1;
+        let name = record
+            .get(8..)
+            .and_then(unistr)
+            .ok_or_else(|| Error::InvalidFormat("corrupt HFS+ thread record".to_string()))?;
+        let parent = be_u32(record, 4);
+        self.lookup(parent, &name)?
+            .filter(|entry| entry.id == id)
+            .ok_or_else(|| Error::NotFound(format!("HFS+ catalog node {}", id)))
+    }
+
+    /// Volume name, the name of the root folder
+    pub(super) fn volume_name(&self) -> Result<String> {
+        Ok(self.entry(ROOT_FOLDER_ID)?.name)
+    }
+
+    fn lookup(&self, parent: u32, name: &str) -> Result<Option<Entry>> {
+        let entries = self.children(parent)?;
+        if let Some(i) = entries.iter().position(|entry| entry.name == name) {
+            return Ok(entries.into_iter().nth(i));
+        }
+        if !self.case_insensitive {
+            return Ok(None);
+        }
+        let folded = name.to_lowercase();
+        Ok(entries
+            .into_iter()
+            .find(|entry| entry.name.to_lowercase() == folded))
+    }
+
+    /// The inode a hard link points at, or the entry itself
+    fn follow_hard_link(&self, entry: Entry) -> Result<Entry> {
+        let Some((dir, name)) = entry.link_inode() else {
+            return Ok(entry);
+        };
+        let inode = match self.lookup(ROOT_FOLDER_ID, dir)? {
+            Some(dir) => self.lookup(dir.id, &name)?,
+            None => None,
+        };
+        let mut inode = inode
+            .ok_or_else(|| Error::InvalidFormat(format!("HFS+ hard link to missing {}", name)))?;
+        inode.links = inode.special.max(1);
+        Ok(inode)
+    }
+
+    /// Entry at `path`, following a final symbolic link if `follow`
+    pub(super) fn resolve(&self, path: &str, follow: bool) -> Result<Entry> {
+        let mut pending: Vec<String> = components(path).rev().collect();
+        let mut current = self.entry(ROOT_FOLDER_ID)?;
+        let mut symlinks = 0;
+        while let Some(name) = pending.pop() {
+            if !current.is_dir() {
+                return Err(Error::NotFound(format!("{}: not a directory", path)));
+            }
+            if name == ".." {
+                if current.id != ROOT_FOLDER_ID {
+                    current = self.entry(current.parent)?;
+                }
+                continue;
+            }
+            let entry = self
+                .lookup(current.id, &name)?
+                .ok_or_else(|| Error::NotFound(path.to_string()))?;
+            let entry = self.follow_hard_link(entry)?;
+            if entry.is_symlink() && (follow || !pending.is_empty()) {
+                symlinks += 1;
+                if symlinks > MAX_SYMLINKS {
+                    return Err(Error::InvalidFormat(format!(
+                        "{}: too many levels of symbolic links",
+                        path
+                    )));
+                }
+                let target = self.link_target(&entry)?;
+                if target.starts_with(b"/") {
+                    current = self.entry(ROOT_FOLDER_ID)?;
+                }
+                let target = String::from_utf8_lossy(&target).into_owned();
+                pending.extend(components(&target).rev());
+                continue;
+            }
+            current = entry;
+        }
+        Ok(current)
+    }
+
+    pub(super) fn link_target(&self, entry: &Entry) -> Result<Vec<u8>> {
+        if entry.data.size > MAX_LINK_LEN {
+            return Err(Error::InvalidFormat(format!(
+                "HFS+ file {}: symbolic link of {} bytes",
+                entry.id, entry.data.size
+            )));
+        }
+        self.read_fork(entry.id, super::FORK_DATA, &entry.data)
+    }
+
+    /// Value of extended attribute `name` of `id`, if it has it
+    pub(super) fn xattr(&self, id: u32, name: &str) -> Result<Option<Vec<u8>>> {
+        let Some(attributes) = &self.attributes else {
+            return Ok(None);
+        };
+        let records = self.scan(attributes, &|key: &[u8]| {
+            if key.len() < 8 {
+                return std::cmp::Ordering::Less;
+            }
+            be_u32(key, 4).cmp(&id)
+        })?;
+        let corrupt = || Error::InvalidFormat(format!("HFS+ file {}: corrupt attribute", id));
+        for (key, record) in records {
+            let key_name = key.get(12..).and_then(unistr).ok_or_else(corrupt)?;
+            if key_name != name || record.len() < 16 {
+                continue;
+            }
+            return match be_u32(&record, 0) {
+                ATTR_INLINE_DATA => {
+                    let size = be_u32(&record, 12) as usize;
+                    let data = record.get(16..16 + size).ok_or_else(corrupt)?;
+                    Ok(Some(data.to_vec()))
+                }
+                ATTR_FORK_DATA if record.len() >= 88 => {
+                    let fork = Fork::parse(&record[8..88]);
+                    if !fork.complete() {
+                        return Err(Error::Unsupported(format!(
+                            "HFS+ file {}: attribute {} in extents overflow",
+                            id, name
+                        )));
+                    }
+                    self.read_extents_data(&fork.extents, fork.size).map(Some)
+                }
+                _ => Err(corrupt()),
+            };
+        }
+        Ok(None)
+    }
+
+    /// Content of a regular file, decompressed if stored compressed
+    pub(super) fn read_data(&self, entry: &Entry) -> Result<Vec<u8>> {
+        if u32::from(entry.owner_flags) & decmpfs::UF_COMPRESSED != 0 {
+            if let Some(header) = self.xattr(entry.id, decmpfs::XATTR)? {
+                return decmpfs::decompress(&header, || {
+                    self.read_fork(entry.id, super::FORK_RESOURCE, &entry.resource)
+                });
+            }
+        }
+        self.read_fork(entry.id, super::FORK_DATA, &entry.data)
+    }
+
+    /// Size of the content of a regular file, compressed or not
+    pub(super) fn data_size(&self, entry: &Entry) -> Result<u64> {
+        if u32::from(entry.owner_flags) & decmpfs::UF_COMPRESSED != 0 {
+            if let Some(header) = self.xattr(entry.id, decmpfs::XATTR)? {
+                return Ok(decmpfs::uncompressed_size(&header).unwrap_or(0));
+            }
+        }
+        Ok(entry.data.size)
+    }
+}
+
+fn components(path: &str) -> impl DoubleEndedIterator<Item = String> + '_ {
+    path.split('/')
+        .filter(|name| !name.is_empty() && *name != ".")
+        .map(str::to_string)
+}
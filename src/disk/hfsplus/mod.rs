@@ -0,0 +1,574 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Pure Rust HFS+ reader
+//!
+//! Reads HFS+ and HFSX volumes in disk images without mounting them, for
+//! macOS guests from before APFS (10.12 and older) and for the HFS+
+//! volumes later ones still carry, such as installers and Recovery HD.
+//!
+//! Case-insensitive and case-sensitive (HFSX) volumes, hard links, and
+//! files compressed with zlib or LZVN are read. The journal isn't
+//! replayed, so a volume that wasn't cleanly unmounted reads as of its
+//! last checkpoint; HFS+ volumes wrapped in a classic HFS volume are
+//! refused.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use guestkit::disk::HfsPlus;
+//!
+//! // A macOS image with its system volume in a partition at 200 MiB
+//! let fs = HfsPlus::open("/srv/images/sierra.img", 200 * 1024 * 1024)?;
+//! let plist = fs.read_file("/System/Library/CoreServices/SystemVersion.plist")?;
+//! for entry in fs.list_dir("/Applications")? {
+//!     println!("{}", entry.name);
+//! }
+//! # Ok::<(), guestkit::Error>(())
+//! ```
+
+mod btree;
+mod catalog;
+
+use crate::core::{Error, Result};
+use crate::disk::DiskReader;
+use btree::BTree;
+use serde::Serialize;
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+
+const VOLUME_HEADER_OFFSET: u64 = 1024;
+const VOLUME_HEADER_SIZE: usize = 512;
+const SIGNATURE_HFSPLUS: &[u8; 2] = b"H+";
+const SIGNATURE_HFSX: &[u8; 2] = b"HX";
+/// A classic HFS volume, possibly wrapping an HFS+ one
+const SIGNATURE_HFS: &[u8; 2] = b"BD";
+
+const CATALOG_FILE_ID: u32 = 4;
+const FORK_DATA: u8 = 0x00;
+const FORK_RESOURCE: u8 = 0xff;
+const FORK_DATA_SIZE: usize = 80;
+const EXTENT_COUNT: usize = 8;
+
+/// File metadata, as `lstat` reports it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HfsPlusStat {
+    pub ino: u64,
+    /// File type and permission bits
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    pub links: u32,
+    pub mtime: i64,
+}
+
+/// A directory entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HfsPlusDirEntry {
+    pub name: String,
+    pub ino: u64,
+    /// Type of the entry, as in `d_type`: 4 directory, 8 regular file,
+    /// 10 symbolic link
+    pub file_type: u8,
+}
+
+fn be_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn be_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Extent {
+    start: u32,
+    count: u32,
+}
+
+/// Up to eight extents, ending at the first empty one
+fn parse_extents(raw: &[u8]) -> Vec<Extent> {
+    raw.chunks_exact(8)
+        .take(EXTENT_COUNT)
+        .map(|extent| Extent {
+            start: be_u32(extent, 0),
+            count: be_u32(extent, 4),
+        })
+        .take_while(|extent| extent.count != 0)
+        .collect()
+}
+
+/// Size and first extents of a data or resource fork
+#[derive(Debug, Clone, Default)]
+struct Fork {
+    size: u64,
+    blocks: u32,
+    extents: Vec<Extent>,
+}
+
+impl Fork {
+    fn parse(raw: &[u8]) -> Self {
+        Self {
+            size: u64::from_be_bytes(raw[0..8].try_into().unwrap()),
+            blocks: be_u32(raw, 12),
+            extents: parse_extents(&raw[16..FORK_DATA_SIZE]),
+        }
+    }
+
+    /// Whether the extents in the fork data cover all of it
+    fn complete(&self) -> bool {
+        self.extents
+            .iter()
+            .map(|extent| u64::from(extent.count))
+            .sum::<u64>()
+            >= u64::from(self.blocks)
+    }
+}
+
+/// Where the volume is: a raw image or device, or any image through the
+/// disk reader
+enum Source {
+    File(File),
+    Reader(Box<DiskReader>),
+}
+
+impl Source {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        match self {
+            Source::File(file) => file.read_exact_at(buf, offset).map_err(Error::Io),
+            Source::Reader(reader) => reader.pread_exact(offset, buf),
+        }
+    }
+}
+
+/// An HFS+ volume at `offset` of a disk image
+pub struct HfsPlus {
+    source: Source,
+    offset: u64,
+    block_size: u64,
+    total_blocks: u64,
+    extents: BTree,
+    catalog: BTree,
+    attributes: Option<BTree>,
+    case_insensitive: bool,
+    name: String,
+}
+
+impl HfsPlus {
+    /// Open the volume at `offset` of a raw image or device
+    pub fn open<P: AsRef<Path>>(path: P, offset: u64) -> Result<Self> {
+        let file = File::open(path.as_ref())?;
+        Self::load(Source::File(file), offset)
+    }
+
+    /// Read the volume at `offset` of any image the disk reader opens
+    pub fn from_reader(reader: DiskReader, offset: u64) -> Result<Self> {
+        Self::load(Source::Reader(Box::new(reader)), offset)
+    }
+
+    fn load(source: Source, offset: u64) -> Result<Self> {
+        let mut header = vec![0u8; VOLUME_HEADER_SIZE];
+        source.read_at(offset + VOLUME_HEADER_OFFSET, &mut header)?;
+        let signature = &header[0..2];
+        if signature == SIGNATURE_HFS {
+            return Err(Error::Unsupported(
+                "HFS volumes and HFS+ volumes wrapped in them".to_string(),
+            ));
+        }
+        if signature != SIGNATURE_HFSPLUS && signature != SIGNATURE_HFSX {
+            return Err(Error::InvalidFormat("not an HFS+ volume".to_string()));
+        }
+        let block_size = u64::from(be_u32(&header, 40));
+        if !block_size.is_power_of_two() || block_size < 512 {
+            return Err(Error::InvalidFormat(format!(
+                "HFS+ block size {}",
+                block_size
+            )));
+        }
+
+        let mut fs = Self {
+            source,
+            offset,
+            block_size,
+            total_blocks: u64::from(be_u32(&header, 44)),
+            extents: BTree::default(),
+            catalog: BTree::default(),
+            attributes: None,
+            case_insensitive: true,
+            name: String::new(),
+        };
+        // The extents overflow file can't overflow itself
+        let extents = Fork::parse(&header[192..272]);
+        if !extents.complete() {
+            return Err(Error::InvalidFormat(
+                "HFS+ extents overflow file is incomplete".to_string(),
+            ));
+        }
+        fs.extents = fs.open_btree(extents.extents, "extents overflow")?;
+        let catalog = Fork::parse(&header[272..352]);
+        let catalog = fs.fork_extents(CATALOG_FILE_ID, FORK_DATA, &catalog)?;
+        fs.catalog = fs.open_btree(catalog, "catalog")?;
+        let attributes = Fork::parse(&header[352..432]);
+        if attributes.blocks != 0 {
+            if !attributes.complete() {
+                return Err(Error::Unsupported(
+                    "HFS+ attributes file in extents overflow".to_string(),
+                ));
+            }
+            fs.attributes = Some(fs.open_btree(attributes.extents, "attributes")?);
+        }
+        // HFSX volumes may compare names as binary, case-sensitively
+        fs.case_insensitive = signature == SIGNATURE_HFSPLUS || !fs.catalog.binary_keys();
+        fs.name = fs.volume_name()?;
+        Ok(fs)
+    }
+
+    pub fn label(&self) -> String {
+        self.name.clone()
+    }
+
+    pub fn block_size(&self) -> u64 {
+        self.block_size
+    }
+
+    /// Metadata of `path`, not following a final symbolic link
+    pub fn stat(&self, path: &str) -> Result<HfsPlusStat> {
+        let entry = self.resolve(path, false)?;
+        Ok(HfsPlusStat {
+            ino: u64::from(entry.id),
+            mode: u32::from(entry.mode),
+            uid: entry.uid,
+            gid: entry.gid,
+            size: self.data_size(&entry)?,
+            links: entry.links,
+            mtime: entry.mtime,
+        })
+    }
+
+    pub fn exists(&self, path: &str) -> Result<bool> {
+        match self.resolve(path, false) {
+            Ok(_) => Ok(true),
+            Err(Error::NotFound(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn read_file(&self, path: &str) -> Result<Vec<u8>> {
+        let entry = self.resolve(path, true)?;
+        if !entry.is_reg() {
+            return Err(Error::InvalidOperation(format!(
+                "{}: not a regular file",
+                path
+            )));
+        }
+        self.read_data(&entry)
+    }
+
+    pub fn read_link(&self, path: &str) -> Result<String> {
+        let entry = self.resolve(path, false)?;
+        if !entry.is_symlink() {
+            return Err(Error::InvalidOperation(format!(
+                "{}: not a symbolic link",
+                path
+            )));
+        }
+        let target = self.link_target(&entry)?;
+        Ok(String::from_utf8_lossy(&target).into_owned())
+    }
+
+    /// Entries of directory `path`; HFS+ stores no "." or ".."
+    pub fn list_dir(&self, path: &str) -> Result<Vec<HfsPlusDirEntry>> {
+        let dir = self.resolve(path, true)?;
+        if !dir.is_dir() {
+            return Err(Error::NotFound(format!("{}: not a directory", path)));
+        }
+        Ok(self
+            .children(dir.id)?
+            .into_iter()
+            .map(|entry| HfsPlusDirEntry {
+                file_type: entry.file_type(),
+                name: entry.name,
+                ino: u64::from(entry.id),
+            })
+            .collect())
+    }
+
+    /// All extents of a fork, the ones past the first eight from the
+    /// extents overflow file
+    fn fork_extents(&self, id: u32, fork_type: u8, fork: &Fork) -> Result<Vec<Extent>> {
+        let mut extents = fork.extents.clone();
+        let mut covered: u64 = extents.iter().map(|extent| u64::from(extent.count)).sum();
+        while covered < u64::from(fork.blocks) {
+            let start = covered as u32;
+            let records = self.scan(&self.extents, &|key: &[u8]| {
+                if key.len() < 12 {
+                    return std::cmp::Ordering::Less;
+                }
+                (be_u32(key, 4), key[2], be_u32(key, 8)).cmp(&(id, fork_type, start))
+            })?;
+            let more = records
+                .first()
+                .map(|(_, record)| parse_extents(record))
+                .unwrap_or_default();
+            if more.is_empty() {
+                return Err(Error::InvalidFormat(format!(
+                    "HFS+ file {}: extents missing from block {}",
+                    id, start
+                )));
+            }
+            covered += more
+                .iter()
+                .map(|extent| u64::from(extent.count))
+                .sum::<u64>();
+            extents.extend(more);
+        }
+        Ok(extents)
+    }
+
+    fn read_fork(&self, id: u32, fork_type: u8, fork: &Fork) -> Result<Vec<u8>> {
+        let extents = self.fork_extents(id, fork_type, fork)?;
+        self.read_extents_data(&extents, fork.size)
+    }
+
+    fn read_extents_data(&self, extents: &[Extent], size: u64) -> Result<Vec<u8>> {
+        let len = usize::try_from(size)
+            .map_err(|_| Error::ResourceLimit(format!("HFS+ fork of {} bytes", size)))?;
+        let mut data = vec![0u8; len];
+        self.read_extents(extents, 0, &mut data)?;
+        Ok(data)
+    }
+
+    /// Read `buf.len()` bytes at `offset` of the file stored in `extents`
+    fn read_extents(&self, extents: &[Extent], offset: u64, buf: &mut [u8]) -> Result<()> {
+        let mut done = 0;
+        let mut extent_start = 0;
+        for extent in extents {
+            if done == buf.len() {
+                break;
+            }
+            if u64::from(extent.start) + u64::from(extent.count) > self.total_blocks {
+                return Err(Error::InvalidFormat(format!(
+                    "HFS+ extent at block {} past the end of the volume",
+                    extent.start
+                )));
+            }
+            let len = u64::from(extent.count) * self.block_size;
+            let pos = offset + done as u64;
+            if pos < extent_start + len {
+                let within = pos - extent_start;
+                let n = (len - within).min((buf.len() - done) as u64) as usize;
+                self.source.read_at(
+                    self.offset + u64::from(extent.start) * self.block_size + within,
+                    &mut buf[done..done + n],
+                )?;
+                done += n;
+            }
+            extent_start += len;
+        }
+        if done < buf.len() {
+            return Err(Error::InvalidFormat(
+                "HFS+ read past the extents of a file".to_string(),
+            ));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    const BLOCK: usize = 4096;
+    const SYSTEM: u32 = 16;
+    const HELLO: u32 = 17;
+    const LINK: u32 = 18;
+    const PLIST: u32 = 19;
+    const HARD: u32 = 20;
+    const INODE: u32 = 21;
+    const PRIVATE: u32 = 22;
+
+    fn unistr(name: &str) -> Vec<u8> {
+        let units: Vec<u16> = name.encode_utf16().collect();
+        let mut raw = (units.len() as u16).to_be_bytes().to_vec();
+        raw.extend(units.iter().flat_map(|unit| unit.to_be_bytes()));
+        raw
+    }
+
+    fn node(kind: i8, records: &[Vec<u8>]) -> Vec<u8> {
+        let mut node = vec![0u8; BLOCK];
+        node[8] = kind as u8;
+        node[10..12].copy_from_slice(&(records.len() as u16).to_be_bytes());
+        let mut at = 14;
+        for (i, record) in records.iter().enumerate() {
+            node[at..at + record.len()].copy_from_slice(record);
+            node[BLOCK - 2 * (i + 1)..BLOCK - 2 * i].copy_from_slice(&(at as u16).to_be_bytes());
+            at += record.len();
+        }
+        let free = BLOCK - 2 * (records.len() + 1);
+        node[free..free + 2].copy_from_slice(&(at as u16).to_be_bytes());
+        node
+    }
+
+    /// A header node for a tree of `nodes` nodes with its root in node 1
+    fn header_node(nodes: u32) -> Vec<u8> {
+        let mut header = vec![0u8; 106];
+        if nodes > 1 {
+            header[0..2].copy_from_slice(&1u16.to_be_bytes());
+            header[2..6].copy_from_slice(&1u32.to_be_bytes());
+        }
+        header[18..20].copy_from_slice(&(BLOCK as u16).to_be_bytes());
+        header[20..22].copy_from_slice(&516u16.to_be_bytes());
+        header[22..26].copy_from_slice(&nodes.to_be_bytes());
+        header[37] = 0xcf;
+        header[38..42].copy_from_slice(&0x6u32.to_be_bytes());
+        node(1, &[header])
+    }
+
+    fn catalog_key(parent: u32, name: &str) -> Vec<u8> {
+        let name = unistr(name);
+        let mut key = ((4 + name.len()) as u16).to_be_bytes().to_vec();
+        key.extend_from_slice(&parent.to_be_bytes());
+        key.extend_from_slice(&name);
+        key
+    }
+
+    fn folder(parent: u32, name: &str, id: u32) -> Vec<u8> {
+        let mut record = catalog_key(parent, name);
+        let mut folder = vec![0u8; 88];
+        folder[0..2].copy_from_slice(&1u16.to_be_bytes());
+        folder[8..12].copy_from_slice(&id.to_be_bytes());
+        folder[42..44].copy_from_slice(&0o40755u16.to_be_bytes());
+        record.extend_from_slice(&folder);
+        record
+    }
+
+    fn thread(id: u32, parent: u32, name: &str) -> Vec<u8> {
+        let mut record = catalog_key(id, "");
+        record.extend_from_slice(&3u16.to_be_bytes());
+        record.extend_from_slice(&[0; 2]);
+        record.extend_from_slice(&parent.to_be_bytes());
+        record.extend_from_slice(&unistr(name));
+        record
+    }
+
+    /// A file record; `data` is its size and single block
+    fn file(parent: u32, name: &str, id: u32, mode: u16, data: (u64, u32)) -> Vec<u8> {
+        let mut record = catalog_key(parent, name);
+        let mut file = vec![0u8; 248];
+        file[0..2].copy_from_slice(&2u16.to_be_bytes());
+        file[8..12].copy_from_slice(&id.to_be_bytes());
+        // 2023-11-14, in seconds since 1904
+        file[16..20].copy_from_slice(&3_782_844_800u32.to_be_bytes());
+        file[32..36].copy_from_slice(&501u32.to_be_bytes());
+        file[42..44].copy_from_slice(&mode.to_be_bytes());
+        file[88..96].copy_from_slice(&data.0.to_be_bytes());
+        if data.0 != 0 {
+            file[100..104].copy_from_slice(&1u32.to_be_bytes());
+            file[104..108].copy_from_slice(&data.1.to_be_bytes());
+            file[108..112].copy_from_slice(&1u32.to_be_bytes());
+        }
+        record.extend_from_slice(&file);
+        record
+    }
+
+    /// A volume of 16 blocks: /hello, /link -> System/version.plist,
+    /// the plist compressed and /hard, a hard link to hello's data
+    fn image() -> Vec<u8> {
+        let mut image = vec![0u8; 16 * BLOCK];
+        let vh = &mut image[1024..1536];
+        vh[0..2].copy_from_slice(b"H+");
+        vh[2..4].copy_from_slice(&4u16.to_be_bytes());
+        vh[40..44].copy_from_slice(&(BLOCK as u32).to_be_bytes());
+        vh[44..48].copy_from_slice(&16u32.to_be_bytes());
+        for (fork, start, blocks) in [(192, 1u32, 1u32), (272, 2, 2), (352, 4, 2)] {
+            let size = u64::from(blocks) * BLOCK as u64;
+            vh[fork..fork + 8].copy_from_slice(&size.to_be_bytes());
+            vh[fork + 12..fork + 16].copy_from_slice(&blocks.to_be_bytes());
+            vh[fork + 16..fork + 20].copy_from_slice(&start.to_be_bytes());
+            vh[fork + 20..fork + 24].copy_from_slice(&blocks.to_be_bytes());
+        }
+        image[BLOCK..2 * BLOCK].copy_from_slice(&header_node(1));
+
+        let mut hard = file(2, "hard", HARD, 0o100644, (0, 0));
+        let at = hard.len() - 248;
+        hard[at + 44..at + 48].copy_from_slice(&INODE.to_be_bytes());
+        hard[at + 48..at + 56].copy_from_slice(b"hlnkhfs+");
+        let mut inode = file(PRIVATE, "iNode21", INODE, 0o100644, (6, 6));
+        let at = inode.len() - 248;
+        inode[at + 44..at + 48].copy_from_slice(&2u32.to_be_bytes());
+        let mut plist = file(SYSTEM, "version.plist", PLIST, 0o100644, (0, 0));
+        let at = plist.len() - 248;
+        plist[at + 41] = 0x20;
+        let records = [
+            folder(1, "Macintosh HD", 2),
+            thread(2, 1, "Macintosh HD"),
+            folder(2, "\0\0\0\0HFS+ Private Data", PRIVATE),
+            hard,
+            file(2, "hello", HELLO, 0o100644, (6, 6)),
+            file(2, "link", LINK, 0o120755, (20, 7)),
+            folder(2, "System", SYSTEM),
+            plist,
+            inode,
+        ];
+        image[2 * BLOCK..3 * BLOCK].copy_from_slice(&header_node(2));
+        image[3 * BLOCK..4 * BLOCK].copy_from_slice(&node(-1, &records));
+
+        let name = unistr("com.apple.decmpfs");
+        let mut record = ((10 + name.len()) as u16).to_be_bytes().to_vec();
+        record.extend_from_slice(&[0; 2]);
+        record.extend_from_slice(&PLIST.to_be_bytes());
+        record.extend_from_slice(&[0; 4]);
+        record.extend_from_slice(&name);
+        let mut header = b"fpmc".to_vec();
+        header.extend_from_slice(&1u32.to_le_bytes());
+        header.extend_from_slice(&8u64.to_le_bytes());
+        header.extend_from_slice(b"<plist/>");
+        record.extend_from_slice(&0x10u32.to_be_bytes());
+        record.extend_from_slice(&[0; 8]);
+        record.extend_from_slice(&(header.len() as u32).to_be_bytes());
+        record.extend_from_slice(&header);
+        image[4 * BLOCK..5 * BLOCK].copy_from_slice(&header_node(2));
+        image[5 * BLOCK..6 * BLOCK].copy_from_slice(&node(-1, &[record]));
+
+        image[6 * BLOCK..6 * BLOCK + 6].copy_from_slice(b"hello\n");
+        image[7 * BLOCK..7 * BLOCK + 20].copy_from_slice(b"System/version.plist");
+        image
+    }
+
+    #[test]
+    fn test_read_volume() {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&image()).unwrap();
+        let fs = HfsPlus::open(file.path(), 0).unwrap();
+        assert_eq!(fs.label(), "Macintosh HD");
+
+        let names: Vec<_> = fs
+            .list_dir("/")
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.name, entry.file_type))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                ("\0\0\0\0HFS+ Private Data".to_string(), 4),
+                ("hard".to_string(), 8),
+                ("hello".to_string(), 8),
+                ("link".to_string(), 10),
+                ("System".to_string(), 4),
+            ]
+        );
+        assert_eq!(fs.read_file("/hello").unwrap(), b"hello\n");
+        assert_eq!(fs.read_file("/System/../HELLO").unwrap(), b"hello\n");
+        assert_eq!(fs.read_file("/link").unwrap(), b"<plist/>");
+        assert_eq!(fs.read_link("/link").unwrap(), "System/version.plist");
+        assert_eq!(fs.read_file("/hard").unwrap(), b"hello\n");
+
+        let stat = fs.stat("/hard").unwrap();
+        assert_eq!((stat.ino, stat.links, stat.uid), (u64::from(INODE), 2, 501));
+        assert_eq!(stat.mtime, 1_700_000_000);
+        assert_eq!(fs.stat("/System/version.plist").unwrap().size, 8);
+        assert!(!fs.exists("/nope").unwrap());
+    }
+}
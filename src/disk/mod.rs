@@ -4,14 +4,36 @@
 //! This module provides pure Rust implementations for reading disk images,
 //! parsing partition tables, and detecting filesystems.
 
+pub mod alignment;
+pub mod allocation;
+pub mod apfs;
+pub mod boot_mode;
+pub mod ext4;
 pub mod filesystem;
+pub mod hfsplus;
 pub mod loop_device;
 pub mod nbd;
 pub mod partition;
+pub mod qcow2;
 pub mod reader;
+pub mod vhdx;
+pub mod vmdk;
+pub mod xfs;
 
+mod decmpfs;
+
+pub use alignment::AlignmentReport;
+pub use allocation::Extent;
+pub use apfs::{Apfs, ApfsDirEntry, ApfsFs, ApfsStat, ApfsVolume};
+pub use boot_mode::{BootLayout, BootMode};
+pub use ext4::{Ext4, Ext4Deleted, Ext4DirEntry, Ext4Stat};
 pub use filesystem::{FileSystem, FileSystemType};
+pub use hfsplus::{HfsPlus, HfsPlusDirEntry, HfsPlusStat};
 pub use loop_device::LoopDevice;
 pub use nbd::NbdDevice;
 pub use partition::{Partition, PartitionTable, PartitionType};
+pub use qcow2::{Qcow2Image, Qcow2Reader};
 pub use reader::DiskReader;
+pub use vhdx::VhdxImage;
+pub use vmdk::{VmdkImage, VmdkReader, VmdkSubformat};
+pub use xfs::{Xfs, XfsDirEntry, XfsStat};
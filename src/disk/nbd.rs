@@ -6,6 +6,7 @@
 //! full filesystem parsers.
 
 use crate::core::{Error, Result};
+use crate::disk::vmdk::VmdkImage;
 use std::path::{Path, PathBuf};
 use std::process::{Child, Command};
 use std::thread;
@@ -244,6 +245,22 @@ impl NbdDevice {
             })
             .unwrap_or("raw");
 
+        // QEMU reads streamOptimized and seSparse VMDKs but cannot write
+        // them; check the variant and the snapshot chain up front
+        if format == "vmdk" {
+            let vmdk = VmdkImage::open(image_path)?;
+            vmdk.backing_chain()?;
+            if !_read_only && !vmdk.is_writable() {
+                return Err(Error::Unsupported(format!(
+                    "{} is a {} VMDK, which can only be opened read-only. \
+                     Convert it first: guestctl convert {} -o disk.qcow2",
+                    image_path.display(),
+                    vmdk.subformat,
+                    image_path.display()
+                )));
+            }
+        }
+
         // Use short flags: -c instead of --connect, -f instead of --format
         // This is important! Long flags cause qemu-nbd to exit immediately
         cmd.arg("-c").arg(&self.device_path)
@@ -40,26 +40,36 @@ pub struct Partition {
 pub struct PartitionTable {
     partitions: Vec<Partition>,
     table_type: PartitionType,
+    sector_size: u64,
 }
 
 impl PartitionTable {
     /// Parse partition table from disk
     pub fn parse(reader: &mut DiskReader) -> Result<Self> {
+        Self::parse_with_sector_size(reader, 512)
+    }
+
+    /// Parse a partition table written for `sector_size`-byte sectors
+    /// (4096 for 4Kn disks); LBAs of the partitions are in those sectors
+    pub fn parse_with_sector_size(reader: &mut DiskReader, sector_size: u64) -> Result<Self> {
         // Read first sector (MBR/protective MBR)
         let mut mbr_sector = vec![0u8; 512];
         reader.read_exact_at(0, &mut mbr_sector)?;
 
         // Check for GPT signature
-        if Self::is_gpt(&mbr_sector) {
-            Self::parse_gpt(reader)
+        let mut table = if Self::is_gpt(&mbr_sector) {
+            Self::parse_gpt(reader, sector_size)?
         } else if Self::is_mbr(&mbr_sector) {
-            Self::parse_mbr(&mbr_sector)
+            Self::parse_mbr(&mbr_sector)?
         } else {
-            Ok(Self {
+            Self {
                 partitions: Vec::new(),
                 table_type: PartitionType::Unknown,
-            })
-        }
+                sector_size,
+            }
+        };
+        table.sector_size = sector_size;
+        Ok(table)
     }
 
     /// Check if disk has GPT
@@ -121,14 +131,15 @@ impl PartitionTable {
         Ok(Self {
             partitions,
             table_type: PartitionType::MBR,
+            sector_size: 512,
         })
     }
 
     /// Parse GPT partition table
-    fn parse_gpt(reader: &mut DiskReader) -> Result<Self> {
+    fn parse_gpt(reader: &mut DiskReader, sector_size: u64) -> Result<Self> {
         // Read GPT header (sector 1)
         let mut gpt_header = vec![0u8; 512];
-        reader.read_exact_at(512, &mut gpt_header)?;
+        reader.read_exact_at(sector_size, &mut gpt_header)?;
 
         // Check GPT signature "EFI PART"
         if &gpt_header[0..8] != b"EFI PART" {
@@ -148,7 +159,7 @@ impl PartitionTable {
 
         // Read partition entries
         let mut partitions = Vec::new();
-        let entries_offset = partition_entries_lba * 512;
+        let entries_offset = partition_entries_lba * sector_size;
 
         for i in 0..num_entries.min(128) {
             let offset = entries_offset + (i as u64 * entry_size as u64);
@@ -195,6 +206,7 @@ impl PartitionTable {
         Ok(Self {
             partitions,
             table_type: PartitionType::GPT,
+            sector_size,
         })
     }
 
@@ -207,6 +219,11 @@ impl PartitionTable {
     pub fn table_type(&self) -> &PartitionType {
         &self.table_type
     }
+
+    /// Sector size the partition LBAs are in
+    pub fn sector_size(&self) -> u64 {
+        self.sector_size
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,417 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Guest data of a qcow2 image, read through its L1 and L2 tables
+//!
+//! Each guest cluster is either stored in the file (deflate-compressed or
+//! not), marked as reading zeros, or unallocated, in which case it comes
+//! from the backing file (zeros without one). Backing files may be raw or
+//! qcow2 themselves.
+
+use super::{Qcow2Image, L1E_OFFSET_MASK, L2E_OFFSET_MASK, OFLAG_COMPRESSED, QCOW2_MAGIC};
+use crate::core::{Error, Result};
+use crate::disk::allocation::{self, Extent, LayerMap};
+use flate2::read::DeflateDecoder;
+use std::fs::File;
+use std::io::Read;
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Standard cluster that reads as zeros (version 3)
+const OFLAG_ZERO: u64 = 1;
+
+/// External data file, zstd compression, extended L2 entries
+const INCOMPAT_UNREADABLE: u64 = (1 << 2) | (1 << 3) | (1 << 4);
+
+/// Header extension naming the backing file format
+const EXT_BACKING_FORMAT: u32 = 0xe279_2aca;
+
+/// Longest backing chain followed before assuming a loop
+const MAX_CHAIN_LENGTH: usize = 64;
+
+/// Reads the guest disk of a qcow2 image, see [`Qcow2Image::reader`]
+pub struct Qcow2Reader {
+    pub path: PathBuf,
+    /// Virtual disk size in bytes
+    pub virtual_size: u64,
+    image: Qcow2Image,
+    l1: Vec<u64>,
+    backing: Option<Backing>,
+    /// Last cluster inflated; reads of compressed images are mostly sequential
+    inflated: Mutex<Option<(u64, Vec<u8>)>>,
+}
+
+enum Backing {
+    Raw { file: File, size: u64 },
+    Qcow2(Box<Qcow2Reader>),
+}
+
+impl Qcow2Image {
+    /// Open the image and its backing chain to read its guest data
+    ///
+    /// Encrypted images, external data files, zstd compression and
+    /// extended L2 entries are not supported, nor are backing files other
+    /// than raw and qcow2.
+    pub fn reader(&self) -> Result<Qcow2Reader> {
+        Qcow2Reader::open(&self.path, 0)
+    }
+}
+
+impl Qcow2Reader {
+    fn open(path: &Path, depth: usize) -> Result<Self> {
+        let image = Qcow2Image::open(path)?;
+        let header = &image.header;
+        if header.crypt_method != 0 {
+            return Err(Error::Unsupported(format!(
+                "{}: reading encrypted qcow2 images",
+                path.display()
+            )));
+        }
+        if header.incompatible_features & INCOMPAT_UNREADABLE != 0 {
+            return Err(Error::Unsupported(format!(
+                "{}: reading qcow2 images with incompatible features {:#x}",
+                path.display(),
+                header.incompatible_features & INCOMPAT_UNREADABLE
+            )));
+        }
+        let l1 = image.read_l1(header.l1_table_offset, header.l1_size)?;
+
+        let backing = match image.backing_file()? {
+            Some(_) if depth + 1 >= MAX_CHAIN_LENGTH => {
+                return Err(Error::InvalidFormat(format!(
+                    "{}: backing chain longer than {} images",
+                    path.display(),
+                    MAX_CHAIN_LENGTH
+                )))
+            }
+            Some(name) => Some(open_backing(&image, &name, depth)?),
+            None => None,
+        };
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            virtual_size: image.virtual_size(),
+            image,
+            l1,
+            backing,
+            inflated: Mutex::new(None),
+        })
+    }
+
+    /// Read guest data; the whole range must lie within the virtual disk
+    pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        let in_range = offset
+            .checked_add(buf.len() as u64)
+            .is_some_and(|end| end <= self.virtual_size);
+        if !in_range {
+            return Err(Error::InputValidation(format!(
+                "Read of {} bytes at {} is beyond the {} byte disk",
+                buf.len(),
+                offset,
+                self.virtual_size
+            )));
+        }
+
+        let cluster_size = self.image.cluster_size();
+        let mut done = 0;
+        while done < buf.len() {
+            let position = offset + done as u64;
+            let in_cluster = position % cluster_size;
+            let length = (buf.len() - done).min((cluster_size - in_cluster) as usize);
+            let part = &mut buf[done..done + length];
+
+            let cluster = position / cluster_size;
+            match self.l2_entry(cluster)? {
+                entry if entry & OFLAG_COMPRESSED != 0 => {
+                    let mut inflated = self.inflated.lock().unwrap_or_else(|e| e.into_inner());
+                    if inflated.as_ref().is_none_or(|(cached, _)| *cached != entry) {
+                        *inflated = Some((entry, self.inflate(cluster, entry)?));
+                    }
+                    let data = &inflated.as_ref().unwrap().1;
+                    part.copy_from_slice(&data[in_cluster as usize..in_cluster as usize + length]);
+                }
+                entry if entry & OFLAG_ZERO != 0 => part.fill(0),
+                entry => match entry & L2E_OFFSET_MASK {
+                    0 => self.read_backing(position, part)?,
+                    host => self
+                        .image
+                        .file
+                        .read_exact_at(part, host + in_cluster)
+                        .map_err(|e| short_read(&self.path, e))?,
+                },
+            }
+            done += length;
+        }
+        Ok(())
+    }
+
+    /// Guest ranges this image or its backing chain has data for
+    pub fn allocation(&self) -> Result<Vec<Extent>> {
+        let cluster_size = self.image.cluster_size();
+        let l2_entries = cluster_size / 8;
+        let span = l2_entries * cluster_size;
+        let mut map = LayerMap::default();
+
+        let mut position = 0;
+        for l1_entry in &self.l1 {
+            if position >= self.virtual_size {
+                break;
+            }
+            let l2_offset = l1_entry & L1E_OFFSET_MASK;
+            if l2_offset == 0 {
+                map.unallocated(position, span.min(self.virtual_size - position));
+                position += span;
+                continue;
+            }
+            for entry in self.image.read_l2(l2_offset)? {
+                if position >= self.virtual_size {
+                    break;
+                }
+                let length = cluster_size.min(self.virtual_size - position);
+                if entry & OFLAG_COMPRESSED != 0 {
+                    map.data(position, length);
+                } else if entry & OFLAG_ZERO == 0 {
+                    match entry & L2E_OFFSET_MASK {
+                        0 => map.unallocated(position, length),
+                        _ => map.data(position, length),
+                    }
+                }
+                position += cluster_size;
+            }
+        }
+        // An L1 table shorter than the disk leaves the rest unallocated
+        if position < self.virtual_size {
+            map.unallocated(position, self.virtual_size - position);
+        }
+
+        map.resolve(self.backing.as_ref().map(|backing| {
+            move || match backing {
+                Backing::Raw { file, size } => allocation::file_extents(file, *size),
+                Backing::Qcow2(reader) => reader.allocation(),
+            }
+        }))
+    }
+
+    /// L2 entry of a guest cluster, 0 if it has none
+    fn l2_entry(&self, cluster: u64) -> Result<u64> {
+        let l2_entries = self.image.cluster_size() / 8;
+        let l2_offset = self
+            .l1
+            .get((cluster / l2_entries) as usize)
+            .map_or(0, |entry| entry & L1E_OFFSET_MASK);
+        if l2_offset == 0 {
+            return Ok(0);
+        }
+        let mut entry = [0u8; 8];
+        self.image
+            .file
+            .read_exact_at(&mut entry, l2_offset + (cluster % l2_entries) * 8)
+            .map_err(|e| short_read(&self.path, e))?;
+        Ok(u64::from_be_bytes(entry))
+    }
+
+    /// Read what the backing file has at `position`; zeros past its end
+    fn read_backing(&self, position: u64, buf: &mut [u8]) -> Result<()> {
+        let size = match &self.backing {
+            Some(Backing::Raw { size, .. }) => *size,
+            Some(Backing::Qcow2(reader)) => reader.virtual_size,
+            None => 0,
+        };
+        let available = (buf.len() as u64).min(size.saturating_sub(position)) as usize;
+        let (head, tail) = buf.split_at_mut(available);
+        match &self.backing {
+            Some(Backing::Raw { file, .. }) => {
+                file.read_exact_at(head, position).map_err(Error::Io)?
+            }
+            Some(Backing::Qcow2(reader)) => reader.read_at(position, head)?,
+            None => {}
+        }
+        tail.fill(0);
+        Ok(())
+    }
+
+    /// Decompress the cluster a compressed L2 entry points to
+    fn inflate(&self, cluster: u64, entry: u64) -> Result<Vec<u8>> {
+        let cluster_bits = self.image.header.cluster_bits;
+        let cluster_size = self.image.cluster_size();
+        // Host offset, then the count of further 512-byte sectors
+        let offset_bits = 62 - (cluster_bits - 8);
+        let offset = entry & ((1 << offset_bits) - 1);
+        let sectors = ((entry >> offset_bits) & ((1 << (cluster_bits - 8)) - 1)) + 1;
+        // The stream may end before its last sector, and the file with it
+        let len = self.image.file.metadata().map_err(Error::Io)?.len();
+        let size = (sectors * 512 - (offset & 511)).min(len.saturating_sub(offset));
+
+        let mut compressed = vec![0u8; size as usize];
+        self.image
+            .file
+            .read_exact_at(&mut compressed, offset)
+            .map_err(|e| short_read(&self.path, e))?;
+        let mut data = Vec::with_capacity(cluster_size as usize);
+        let corrupt = |reason: String| {
+            Error::InvalidFormat(format!(
+                "{}: corrupt compressed cluster {}: {}",
+                self.path.display(),
+                cluster,
+                reason
+            ))
+        };
+        DeflateDecoder::new(compressed.as_slice())
+            .take(cluster_size)
+            .read_to_end(&mut data)
+            .map_err(|e| corrupt(e.to_string()))?;
+        if (data.len() as u64) < cluster_size {
+            return Err(corrupt(format!("{} bytes inflated", data.len())));
+        }
+        Ok(data)
+    }
+}
+
+/// Open the backing file `name` of `image`, raw or qcow2
+fn open_backing(image: &Qcow2Image, name: &str, depth: usize) -> Result<Backing> {
+    let path = match Path::new(name) {
+        path if path.is_absolute() => path.to_path_buf(),
+        path => image
+            .path
+            .parent()
+            .unwrap_or_else(|| Path::new("."))
+            .join(path),
+    };
+    if !path.exists() {
+        return Err(Error::NotFound(format!(
+            "Backing file {} of {} is missing",
+            path.display(),
+            image.path.display()
+        )));
+    }
+
+    let format = image
+        .header
+        .extensions
+        .iter()
+        .find(|(kind, _)| *kind == EXT_BACKING_FORMAT)
+        .map(|(_, data)| String::from_utf8_lossy(data).into_owned());
+    let file = File::open(&path).map_err(Error::Io)?;
+    let mut magic = [0u8; 4];
+    let is_qcow2 = file.read_at(&mut magic, 0).map_err(Error::Io)? == 4 && &magic == QCOW2_MAGIC;
+
+    match (format.as_deref(), is_qcow2) {
+        (Some("qcow2"), _) | (None, true) => Ok(Backing::Qcow2(Box::new(Qcow2Reader::open(
+            &path,
+            depth + 1,
+        )?))),
+        // Without a recorded format QEMU probes; anything but qcow2 is
+        // taken as raw here
+        (Some("raw"), _) | (None, false) => {
+            let size = file.metadata().map_err(Error::Io)?.len();
+            Ok(Backing::Raw { file, size })
+        }
+        (Some(format), _) => Err(Error::Unsupported(format!(
+            "{}: reading {} backing files",
+            image.path.display(),
+            format
+        ))),
+    }
+}
+
+fn short_read(path: &Path, err: std::io::Error) -> Error {
+    if err.kind() == std::io::ErrorKind::UnexpectedEof {
+        Error::InvalidFormat(format!("{} is truncated", path.display()))
+    } else {
+        Error::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{create_image, CLUSTER};
+    use super::*;
+    use flate2::write::DeflateEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    /// Point guest cluster `index` (within the first L2 table, at cluster
+    /// 4) at `entry`
+    fn set_l2_entry(path: &Path, index: u64, entry: u64) {
+        let file = std::fs::OpenOptions::new().write(true).open(path).unwrap();
+        file.write_all_at(&(4 * CLUSTER).to_be_bytes(), 3 * CLUSTER)
+            .unwrap();
+        file.write_all_at(&entry.to_be_bytes(), 4 * CLUSTER + index * 8)
+            .unwrap();
+    }
+
+    fn write_at(path: &Path, offset: u64, data: &[u8]) {
+        let file = std::fs::OpenOptions::new().write(true).open(path).unwrap();
+        file.write_all_at(data, offset).unwrap();
+    }
+
+    #[test]
+    fn test_read_and_allocation() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("base.raw");
+        let mut base_data = vec![0u8; 8 * CLUSTER as usize];
+        base_data[3 * CLUSTER as usize] = 0x44;
+        base_data[5 * CLUSTER as usize + 1] = 0x55;
+        std::fs::write(&base, &base_data).unwrap();
+
+        let path = dir.path().join("overlay.qcow2");
+        create_image(&path, 16 * CLUSTER, Some("base.raw"));
+        // Cluster 0: data at host cluster 5
+        write_at(&path, 5 * CLUSTER, &vec![0x11; CLUSTER as usize]);
+        set_l2_entry(&path, 0, 5 * CLUSTER);
+        // Cluster 1: zeros, hiding the backing file
+        set_l2_entry(&path, 1, OFLAG_ZERO);
+        // Cluster 2: compressed at host cluster 6
+        let mut encoder = DeflateEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(&vec![0x22; CLUSTER as usize]).unwrap();
+        let compressed = encoder.finish().unwrap();
+        write_at(&path, 6 * CLUSTER, &compressed);
+        let sectors = (compressed.len() as u64).div_ceil(512);
+        set_l2_entry(
+            &path,
+            2,
+            OFLAG_COMPRESSED | ((sectors - 1) << 54) | (6 * CLUSTER),
+        );
+        // Cluster 3 and on: from base.raw, zeros past its end
+
+        let reader = Qcow2Image::open(&path).unwrap().reader().unwrap();
+        let mut buf = vec![0u8; 16 * CLUSTER as usize];
+        reader.read_at(0, &mut buf).unwrap();
+        let cluster = |index: usize| &buf[index * CLUSTER as usize..(index + 1) * CLUSTER as usize];
+        assert!(cluster(0).iter().all(|&b| b == 0x11));
+        assert!(cluster(1).iter().all(|&b| b == 0));
+        assert!(cluster(2).iter().all(|&b| b == 0x22));
+        assert_eq!(cluster(3)[0], 0x44);
+        assert_eq!(cluster(5)[1], 0x55);
+        assert!(buf[8 * CLUSTER as usize..].iter().all(|&b| b == 0));
+
+        // Reads across clusters
+        let mut part = [0u8; 4];
+        reader.read_at(CLUSTER - 2, &mut part).unwrap();
+        assert_eq!(part, [0x11, 0x11, 0, 0]);
+        assert!(reader.read_at(16 * CLUSTER - 1, &mut part).is_err());
+
+        // The allocation covers clusters 0 and 2, and base.raw's data
+        let allocation = reader.allocation().unwrap();
+        let covered = |offset: u64| {
+            allocation
+                .iter()
+                .any(|e| e.offset <= offset && offset < e.end())
+        };
+        assert!(covered(0));
+        assert!(!covered(CLUSTER));
+        assert!(covered(2 * CLUSTER));
+        assert!(!covered(8 * CLUSTER));
+        assert!(allocation.iter().all(|e| e.end() <= 16 * CLUSTER));
+    }
+
+    #[test]
+    fn test_missing_backing_file() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("overlay.qcow2");
+        create_image(&path, 16 * CLUSTER, Some("gone.qcow2"));
+        assert!(matches!(
+            Qcow2Image::open(&path).unwrap().reader(),
+            Err(Error::NotFound(_))
+        ));
+    }
+}
@@ -0,0 +1,843 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! qcow2 internal snapshots
+//!
+//! A snapshot is a copy of the L1 table, so it shares the L2 tables and
+//! data clusters of the image; their refcounts count the L1 tables that
+//! reach them, and QEMU copies a cluster before writing it while that
+//! count is above one. Creating, deleting and reverting snapshots is then
+//! a matter of copying L1 tables and adjusting refcounts, as `qemu-img
+//! snapshot` does. Snapshots taken here hold no VM state.
+//!
+//! Refcounts are raised before anything points at the clusters and
+//! lowered only once nothing does, so an interrupted change at worst
+//! leaks clusters.
+
+use super::{
+    be_u32, be_u64, DirectoryEntry, Qcow2Image, BME_TABLE_ALL_ONES, BME_TABLE_OFFSET_MASK,
+    L1E_OFFSET_MASK, L2E_OFFSET_MASK, OFLAG_COMPRESSED,
+};
+use crate::core::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::os::unix::fs::FileExt;
+
+/// External data file, extended L2 entries
+const INCOMPAT_UNSUPPORTED: u64 = (1 << 2) | (1 << 4);
+const AUTOCLEAR_KNOWN: u64 = super::AUTOCLEAR_BITMAPS | super::AUTOCLEAR_DATA_FILE_RAW;
+
+/// The refcount of the cluster is exactly one
+const OFLAG_COPIED: u64 = 1 << 63;
+
+/// Fixed part of a snapshot table entry
+const SNAPSHOT_HEADER_SIZE: usize = 40;
+/// Extra data QEMU writes: VM state size and disk size
+const SNAPSHOT_EXTRA_DATA_SIZE: usize = 16;
+
+/// QEMU's limits for snapshots
+const MAX_SNAPSHOTS: usize = 65536;
+const MAX_SNAPSHOTS_SIZE: u64 = 64 * 1024 * 1024;
+const MAX_SNAPSHOT_NAME_SIZE: usize = 1023;
+
+/// An internal snapshot
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SnapshotInfo {
+    /// Unique ID, a number as QEMU assigns them
+    pub id: String,
+    pub name: String,
+    /// When it was taken, in seconds since the epoch
+    pub date_sec: u32,
+    pub date_nsec: u32,
+    /// Guest clock when it was taken, in nanoseconds
+    pub vm_clock_nsec: u64,
+    /// Bytes of saved VM state; 0 for disk-only snapshots
+    pub vm_state_size: u64,
+    /// Guest disk size when it was taken
+    pub disk_size: u64,
+}
+
+/// Snapshot table entry
+#[derive(Debug, Clone)]
+struct SnapshotEntry {
+    l1_table_offset: u64,
+    l1_size: u32,
+    id: String,
+    name: String,
+    date_sec: u32,
+    date_nsec: u32,
+    vm_clock_nsec: u64,
+    vm_state_size: u32,
+    /// Kept as is, for fields newer than this code
+    extra_data: Vec<u8>,
+}
+
+impl SnapshotEntry {
+    fn info(&self, virtual_size: u64) -> SnapshotInfo {
+        let extra = &self.extra_data;
+        SnapshotInfo {
+            id: self.id.clone(),
+            name: self.name.clone(),
+            date_sec: self.date_sec,
+            date_nsec: self.date_nsec,
+            vm_clock_nsec: self.vm_clock_nsec,
+            vm_state_size: if extra.len() >= 8 {
+                be_u64(extra, 0)
+            } else {
+                u64::from(self.vm_state_size)
+            },
+            disk_size: if extra.len() >= 16 {
+                be_u64(extra, 8)
+            } else {
+                virtual_size
+            },
+        }
+    }
+}
+
+impl Qcow2Image {
+    /// List the internal snapshots
+    pub fn snapshots(&self) -> Result<Vec<SnapshotInfo>> {
+        let size = self.header.size;
+        Ok(self
+            .snapshot_table()?
+            .iter()
+            .map(|entry| entry.info(size))
+            .collect())
+    }
+
+    /// Take a disk-only snapshot of the current contents
+    pub fn create_snapshot(&mut self, name: &str) -> Result<SnapshotInfo> {
+        self.ensure_snapshots_supported()?;
+        let mut table = self.snapshot_table()?;
+        if name.is_empty() || name.len() > MAX_SNAPSHOT_NAME_SIZE {
+            return Err(Error::InputValidation(format!(
+                "Snapshot name must be 1 to {} bytes",
+                MAX_SNAPSHOT_NAME_SIZE
+            )));
+        }
+        if table
+            .iter()
+            .any(|entry| entry.name == name || entry.id == name)
+        {
+            return Err(Error::InputValidation(format!(
+                "Snapshot '{}' already exists in {}",
+                name,
+                self.path.display()
+            )));
+        }
+        if table.len() >= MAX_SNAPSHOTS {
+            return Err(Error::ResourceLimit(format!(
+                "{} already has {} snapshots",
+                self.path.display(),
+                MAX_SNAPSHOTS
+            )));
+        }
+        self.clear_unknown_autoclear()?;
+
+        // The snapshot's own copy of the L1 table; copied flags only mean
+        // something in the active one
+        let l1 = self.read_l1(self.header.l1_table_offset, self.header.l1_size)?;
+        let l1_bytes: Vec<u8> = l1
+            .iter()
+            .flat_map(|entry| (entry & !OFLAG_COPIED).to_be_bytes())
+            .collect();
+        let l1_offset = self.allocate(self.clusters_for(l1_bytes.len() as u64))?;
+        self.file
+            .write_all_at(&l1_bytes, l1_offset)
+            .map_err(Error::Io)?;
+        self.update_refcounts(&l1, 1)?;
+        self.update_copied_flags()?;
+        self.file.sync_data().map_err(Error::Io)?;
+
+        let id = table
+            .iter()
+            .filter_map(|entry| entry.id.parse::<u64>().ok())
+            .max()
+            .unwrap_or(0)
+            + 1;
+        let now = std::time::SystemTime::now()
+            .duration_since(std::time::UNIX_EPOCH)
+            .unwrap_or_default();
+        let mut extra_data = Vec::with_capacity(SNAPSHOT_EXTRA_DATA_SIZE);
+        extra_data.extend_from_slice(&0u64.to_be_bytes());
+        extra_data.extend_from_slice(&self.header.size.to_be_bytes());
+        table.push(SnapshotEntry {
+            l1_table_offset: l1_offset,
+            l1_size: self.header.l1_size,
+            id: id.to_string(),
+            name: name.to_string(),
+            date_sec: now.as_secs() as u32,
+            date_nsec: now.subsec_nanos(),
+            vm_clock_nsec: 0,
+            vm_state_size: 0,
+            extra_data,
+        });
+        self.write_snapshot_table(&table)?;
+
+        Ok(table[table.len() - 1].info(self.header.size))
+    }
+
+    /// Delete the snapshot with ID or name `name`, freeing the clusters
+    /// only it used
+    pub fn delete_snapshot(&mut self, name: &str) -> Result<()> {
+        self.ensure_snapshots_supported()?;
+        let mut table = self.snapshot_table()?;
+        let index = self.find_snapshot(&table, name)?;
+        self.clear_unknown_autoclear()?;
+
+        // Out of the table first, so nothing points at what is freed
+        let entry = table.remove(index);
+        self.write_snapshot_table(&table)?;
+
+        let l1 = self.read_l1(entry.l1_table_offset, entry.l1_size)?;
+        self.update_refcounts(&l1, -1)?;
+        self.free(
+            entry.l1_table_offset,
+            self.clusters_for(u64::from(entry.l1_size) * 8),
+        )?;
+        self.update_copied_flags()?;
+        self.file.sync_data().map_err(Error::Io)
+    }
+
+    /// Revert the disk to the snapshot with ID or name `name`
+    ///
+    /// The snapshot is kept. Dirty bitmaps can't tell what reverting
+    /// changed, so they are marked dirty all over.
+    pub fn revert_snapshot(&mut self, name: &str) -> Result<()> {
+        self.ensure_snapshots_supported()?;
+        let table = self.snapshot_table()?;
+        let entry = &table[self.find_snapshot(&table, name)?];
+        let info = entry.info(self.header.size);
+        if info.disk_size != self.header.size || entry.l1_size > self.header.l1_size {
+            return Err(Error::Unsupported(format!(
+                "Snapshot '{}' is of a {} byte disk, {} is {} bytes now",
+                name,
+                info.disk_size,
+                self.path.display(),
+                self.header.size
+            )));
+        }
+        let directory = self.directory()?;
+        self.ensure_not_in_use(&directory)?;
+        self.clear_unknown_autoclear()?;
+
+        let snapshot_l1 = self.read_l1(entry.l1_table_offset, entry.l1_size)?;
+        let active_l1 = self.read_l1(self.header.l1_table_offset, self.header.l1_size)?;
+        self.update_refcounts(&snapshot_l1, 1)?;
+        self.file.sync_data().map_err(Error::Io)?;
+
+        let mut l1_bytes = vec![0u8; self.header.l1_size as usize * 8];
+        for (index, l1_entry) in snapshot_l1.iter().enumerate() {
+            l1_bytes[index * 8..index * 8 + 8].copy_from_slice(&l1_entry.to_be_bytes());
+        }
+        self.file
+            .write_all_at(&l1_bytes, self.header.l1_table_offset)
+            .map_err(Error::Io)?;
+        self.file.sync_data().map_err(Error::Io)?;
+
+        self.update_refcounts(&active_l1, -1)?;
+        self.update_copied_flags()?;
+        for bitmap in &directory {
+            self.fill_bitmap(bitmap)?;
+        }
+        self.file.sync_data().map_err(Error::Io)
+    }
+
+    fn ensure_snapshots_supported(&self) -> Result<()> {
+        if self.header.incompatible_features & INCOMPAT_UNSUPPORTED != 0 {
+            return Err(Error::Unsupported(format!(
+                "{} uses an external data file or extended L2 entries; \
+                 use qemu-img for its snapshots",
+                self.path.display()
+            )));
+        }
+        Ok(())
+    }
+
+    /// Clear the autoclear bits of other programs before changing the image
+    fn clear_unknown_autoclear(&mut self) -> Result<()> {
+        if self.header.autoclear_features & !AUTOCLEAR_KNOWN != 0 {
+            self.header.autoclear_features &= AUTOCLEAR_KNOWN;
+            self.write_header()?;
+            self.file.sync_data().map_err(Error::Io)?;
+        }
+        Ok(())
+    }
+
+    fn clusters_for(&self, bytes: u64) -> u64 {
+        bytes.div_ceil(self.cluster_size()).max(1)
+    }
+
+    /// Index of the snapshot with ID `name`, or else named `name`
+    fn find_snapshot(&self, table: &[SnapshotEntry], name: &str) -> Result<usize> {
+        table
+            .iter()
+            .position(|entry| entry.id == name)
+            .or_else(|| table.iter().position(|entry| entry.name == name))
+            .ok_or_else(|| {
+                Error::NotFound(format!("Snapshot '{}' in {}", name, self.path.display()))
+            })
+    }
+
+    fn snapshot_table(&self) -> Result<Vec<SnapshotEntry>> {
+        let header = &self.header;
+        if header.nb_snapshots == 0 {
+            return Ok(Vec::new());
+        }
+        let invalid = |message: &str| {
+            Error::InvalidFormat(format!(
+                "{}: snapshot table: {}",
+                self.path.display(),
+                message
+            ))
+        };
+        if header.nb_snapshots as usize > MAX_SNAPSHOTS {
+            return Err(invalid("too many snapshots"));
+        }
+
+        // Entries are read one at a time; the table size isn't recorded
+        let mut entries = Vec::with_capacity(header.nb_snapshots as usize);
+        let mut offset = header.snapshots_offset;
+        for _ in 0..header.nb_snapshots {
+            if offset - header.snapshots_offset > MAX_SNAPSHOTS_SIZE {
+                return Err(invalid("table too large"));
+            }
+            let mut fixed = [0u8; SNAPSHOT_HEADER_SIZE];
+            self.file
+                .read_exact_at(&mut fixed, offset)
+                .map_err(|_| invalid("truncated entry"))?;
+            let id_size = u16::from_be_bytes([fixed[12], fixed[13]]) as usize;
+            let name_size = u16::from_be_bytes([fixed[14], fixed[15]]) as usize;
+            let extra_data_size = be_u32(&fixed, 36) as usize;
+            if extra_data_size > 1024 {
+                return Err(invalid("extra data too large"));
+            }
+            let mut variable = vec![0u8; extra_data_size + id_size + name_size];
+            self.file
+                .read_exact_at(&mut variable, offset + SNAPSHOT_HEADER_SIZE as u64)
+                .map_err(|_| invalid("truncated entry"))?;
+            let text = |bytes: &[u8]| {
+                String::from_utf8(bytes.to_vec()).map_err(|_| invalid("name is not UTF-8"))
+            };
+
+            entries.push(SnapshotEntry {
+                l1_table_offset: be_u64(&fixed, 0),
+                l1_size: be_u32(&fixed, 8),
+                date_sec: be_u32(&fixed, 16),
+                date_nsec: be_u32(&fixed, 20),
+                vm_clock_nsec: be_u64(&fixed, 24),
+                vm_state_size: be_u32(&fixed, 32),
+                extra_data: variable[..extra_data_size].to_vec(),
+                id: text(&variable[extra_data_size..extra_data_size + id_size])?,
+                name: text(&variable[extra_data_size + id_size..])?,
+            });
+            offset += (SNAPSHOT_HEADER_SIZE + variable.len()).next_multiple_of(8) as u64;
+        }
+        Ok(entries)
+    }
+
+    /// Write `table` to new clusters, point the header at it and free the
+    /// old one
+    fn write_snapshot_table(&mut self, table: &[SnapshotEntry]) -> Result<()> {
+        let buf = serialize_snapshot_table(table);
+        if buf.len() as u64 > MAX_SNAPSHOTS_SIZE {
+            return Err(Error::ResourceLimit(format!(
+                "Snapshot table of {} would exceed {} bytes",
+                self.path.display(),
+                MAX_SNAPSHOTS_SIZE
+            )));
+        }
+        let old = match self.header.nb_snapshots {
+            0 => None,
+            _ => Some((self.header.snapshots_offset, self.snapshot_table_size()?)),
+        };
+
+        let offset = if buf.is_empty() {
+            0
+        } else {
+            let offset = self.allocate(self.clusters_for(buf.len() as u64))?;
+            self.file.write_all_at(&buf, offset).map_err(Error::Io)?;
+            self.file.sync_data().map_err(Error::Io)?;
+            offset
+        };
+
+        self.header.nb_snapshots = table.len() as u32;
+        self.header.snapshots_offset = offset;
+        self.write_header()?;
+        self.file.sync_data().map_err(Error::Io)?;
+
+        if let Some((offset, size)) = old {
+            self.free(offset, self.clusters_for(size))?;
+        }
+        Ok(())
+    }
+
+    /// Bytes of the snapshot table in the file
+    fn snapshot_table_size(&self) -> Result<u64> {
+        Ok(serialize_snapshot_table(&self.snapshot_table()?).len() as u64)
+    }
+
+    pub(super) fn read_l1(&self, offset: u64, size: u32) -> Result<Vec<u64>> {
+        let mut buf = vec![0u8; size as usize * 8];
+        self.file
+            .read_exact_at(&mut buf, offset)
+            .map_err(Error::Io)?;
+        Ok(buf.chunks_exact(8).map(|chunk| be_u64(chunk, 0)).collect())
+    }
+
+    pub(super) fn read_l2(&self, offset: u64) -> Result<Vec<u64>> {
+        let mut buf = vec![0u8; self.cluster_size() as usize];
+        self.file
+            .read_exact_at(&mut buf, offset)
+            .map_err(Error::Io)?;
+        Ok(buf.chunks_exact(8).map(|chunk| be_u64(chunk, 0)).collect())
+    }
+
+    /// Write L1 or L2 table entries
+    fn write_table(&self, offset: u64, table: &[u64]) -> Result<()> {
+        let buf: Vec<u8> = table.iter().flat_map(|entry| entry.to_be_bytes()).collect();
+        self.file.write_all_at(&buf, offset).map_err(Error::Io)
+    }
+
+    /// Host clusters an L2 entry references
+    fn data_clusters(&self, l2_entry: u64) -> std::ops::Range<u64> {
+        let cluster_bits = self.header.cluster_bits;
+        if l2_entry & OFLAG_COMPRESSED != 0 {
+            // Host offset, then the count of further 512-byte sectors
+            let offset_bits = 62 - (cluster_bits - 8);
+            let offset = l2_entry & ((1 << offset_bits) - 1);
+            let sectors = ((l2_entry >> offset_bits) & ((1 << (cluster_bits - 8)) - 1)) + 1;
+            let end = (offset & !511) + sectors * 512;
+            return (offset >> cluster_bits)..end.div_ceil(1 << cluster_bits);
+        }
+        match l2_entry & L2E_OFFSET_MASK {
+            0 => 0..0,
+            offset => (offset >> cluster_bits)..(offset >> cluster_bits) + 1,
+        }
+    }
+
+    /// Add `addend` to the refcounts of the L2 tables and data clusters
+    /// `l1` reaches
+    ///
+    /// Data clusters count once per L1 table, even through an L2 table
+    /// several share.
+    fn update_refcounts(&mut self, l1: &[u64], addend: i64) -> Result<()> {
+        for l1_entry in l1 {
+            let l2_offset = l1_entry & L1E_OFFSET_MASK;
+            if l2_offset == 0 {
+                continue;
+            }
+            for l2_entry in self.read_l2(l2_offset)? {
+                for cluster in self.data_clusters(l2_entry) {
+                    self.add_refcount(cluster, addend)?;
+                }
+            }
+            self.add_refcount(l2_offset >> self.header.cluster_bits, addend)?;
+        }
+        Ok(())
+    }
+
+    fn add_refcount(&mut self, cluster: u64, addend: i64) -> Result<()> {
+        let refcount = self.refcount(cluster)?;
+        let max = match self.header.refcount_order {
+            6 => u64::MAX,
+            order => (1 << (1 << order)) - 1,
+        };
+        let updated = refcount
+            .checked_add_signed(addend)
+            .filter(|updated| *updated <= max)
+            .ok_or_else(|| {
+                Error::ResourceLimit(format!(
+                    "Refcount of cluster {} of {} out of range",
+                    cluster,
+                    self.path.display()
+                ))
+            })?;
+        self.set_refcount(cluster, updated)
+    }
+
+    /// Set the copied flags of the active L1 and L2 tables from the
+    /// refcounts, as QEMU expects
+    fn update_copied_flags(&mut self) -> Result<()> {
+        let cluster_bits = self.header.cluster_bits;
+        let mut l1 = self.read_l1(self.header.l1_table_offset, self.header.l1_size)?;
+        let mut l1_changed = false;
+        for l1_entry in l1.iter_mut() {
+            let l2_offset = *l1_entry & L1E_OFFSET_MASK;
+            if l2_offset == 0 {
+                continue;
+            }
+            let mut l2 = self.read_l2(l2_offset)?;
+            let mut l2_changed = false;
+            for l2_entry in l2.iter_mut() {
+                let offset = *l2_entry & L2E_OFFSET_MASK;
+                if *l2_entry & OFLAG_COMPRESSED != 0 || offset == 0 {
+                    continue;
+                }
+                let copied = self.refcount(offset >> cluster_bits)? == 1;
+                let updated = set_flag(*l2_entry, OFLAG_COPIED, copied);
+                l2_changed |= updated != *l2_entry;
+                *l2_entry = updated;
+            }
+            if l2_changed {
+                self.write_table(l2_offset, &l2)?;
+            }
+
+            let copied = self.refcount(l2_offset >> cluster_bits)? == 1;
+            let updated = set_flag(*l1_entry, OFLAG_COPIED, copied);
+            l1_changed |= updated != *l1_entry;
+            *l1_entry = updated;
+        }
+        if l1_changed {
+            self.write_table(self.header.l1_table_offset, &l1)?;
+        }
+        Ok(())
+    }
+
+    /// Mark all of a bitmap dirty, freeing its data clusters
+    fn fill_bitmap(&mut self, bitmap: &DirectoryEntry) -> Result<()> {
+        let table = self.read_table(bitmap)?;
+        let buf: Vec<u8> = table
+            .iter()
+            .flat_map(|_| BME_TABLE_ALL_ONES.to_be_bytes())
+            .collect();
+        self.file
+            .write_all_at(&buf, bitmap.table_offset)
+            .map_err(Error::Io)?;
+        self.file.sync_data().map_err(Error::Io)?;
+        for table_entry in table {
+            let offset = table_entry & BME_TABLE_OFFSET_MASK;
+            if offset != 0 {
+                self.free(offset, 1)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn set_flag(value: u64, flag: u64, set: bool) -> u64 {
+    if set {
+        value | flag
+    } else {
+        value & !flag
+    }
+}
+
+fn serialize_snapshot_table(entries: &[SnapshotEntry]) -> Vec<u8> {
+    let mut buf = Vec::new();
+    for entry in entries {
+        buf.extend_from_slice(&entry.l1_table_offset.to_be_bytes());
+        buf.extend_from_slice(&entry.l1_size.to_be_bytes());
+        buf.extend_from_slice(&(entry.id.len() as u16).to_be_bytes());
+        buf.extend_from_slice(&(entry.name.len() as u16).to_be_bytes());
+        buf.extend_from_slice(&entry.date_sec.to_be_bytes());
+        buf.extend_from_slice(&entry.date_nsec.to_be_bytes());
+        buf.extend_from_slice(&entry.vm_clock_nsec.to_be_bytes());
+        buf.extend_from_slice(&entry.vm_state_size.to_be_bytes());
+        buf.extend_from_slice(&(entry.extra_data.len() as u32).to_be_bytes());
+        buf.extend_from_slice(&entry.extra_data);
+        buf.extend_from_slice(entry.id.as_bytes());
+        buf.extend_from_slice(entry.name.as_bytes());
+        buf.resize(buf.len().next_multiple_of(8), 0);
+    }
+    buf
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::tests::{create_image, CLUSTER};
+    use super::super::DEFAULT_GRANULARITY;
+    use super::*;
+    use std::collections::HashMap;
+
+    const L2_ENTRIES: u64 = CLUSTER / 8;
+
+    fn read_u64(image: &Qcow2Image, offset: u64) -> u64 {
+        let mut buf = [0u8; 8];
+        image.file.read_exact_at(&mut buf, offset).unwrap();
+        u64::from_be_bytes(buf)
+    }
+
+    /// Write guest cluster `index` full of `byte`, copying shared clusters
+    /// first as QEMU does
+    fn write_guest(image: &mut Qcow2Image, index: u64, byte: u8) {
+        let l1_entry_offset = image.header.l1_table_offset + index / L2_ENTRIES * 8;
+        let l1_entry = read_u64(image, l1_entry_offset);
+        let mut l2_offset = l1_entry & L1E_OFFSET_MASK;
+        if l1_entry & OFLAG_COPIED == 0 {
+            let l2 = match l2_offset {
+                0 => vec![0; L2_ENTRIES as usize],
+                offset => image.read_l2(offset).unwrap(),
+            };
+            let copy = image.allocate(1).unwrap();
+            image.write_table(copy, &l2).unwrap();
+            if l2_offset != 0 {
+                image.free(l2_offset, 1).unwrap();
+            }
+            l2_offset = copy;
+            image
+                .write_table(l1_entry_offset, &[copy | OFLAG_COPIED])
+                .unwrap();
+        }
+
+        let mut l2 = image.read_l2(l2_offset).unwrap();
+        let slot = (index % L2_ENTRIES) as usize;
+        if l2[slot] & OFLAG_COPIED == 0 {
+            let old = l2[slot] & L2E_OFFSET_MASK;
+            l2[slot] = image.allocate(1).unwrap() | OFLAG_COPIED;
+            image.write_table(l2_offset, &l2).unwrap();
+            if old != 0 {
+                image.free(old, 1).unwrap();
+            }
+        }
+        image
+            .file
+            .write_all_at(&vec![byte; CLUSTER as usize], l2[slot] & L2E_OFFSET_MASK)
+            .unwrap();
+    }
+
+    /// First byte of guest cluster `index`
+    fn read_guest(image: &Qcow2Image, index: u64) -> u8 {
+        let l1_entry = read_u64(image, image.header.l1_table_offset + index / L2_ENTRIES * 8);
+        if l1_entry & L1E_OFFSET_MASK == 0 {
+            return 0;
+        }
+        let l2_entry = read_u64(image, (l1_entry & L1E_OFFSET_MASK) + index % L2_ENTRIES * 8);
+        if l2_entry & L2E_OFFSET_MASK == 0 {
+            return 0;
+        }
+        let mut byte = [0u8];
+        image
+            .file
+            .read_exact_at(&mut byte, l2_entry & L2E_OFFSET_MASK)
+            .unwrap();
+        byte[0]
+    }
+
+    /// Compare every refcount with the references to the cluster, and the
+    /// copied flags with the refcounts, like `qemu-img check`
+    fn check_image(image: &Qcow2Image) {
+        let header = &image.header;
+        let mut expected: HashMap<u64, u64> = HashMap::new();
+        let mut reference = |offset: u64, clusters: u64| {
+            for cluster in offset / CLUSTER..offset / CLUSTER + clusters {
+                *expected.entry(cluster).or_default() += 1;
+            }
+        };
+
+        reference(0, 1);
+        reference(
+            header.refcount_table_offset,
+            u64::from(header.refcount_table_clusters),
+        );
+        for index in 0..u64::from(header.refcount_table_clusters) * L2_ENTRIES {
+            let block = read_u64(image, header.refcount_table_offset + index * 8);
+            if block != 0 {
+                reference(block, 1);
+            }
+        }
+
+        let mut l1_tables = vec![(header.l1_table_offset, header.l1_size)];
+        let table = image.snapshot_table().unwrap();
+        if !table.is_empty() {
+            let size = serialize_snapshot_table(&table).len() as u64;
+            reference(header.snapshots_offset, image.clusters_for(size));
+        }
+        l1_tables.extend(
+            table
+                .iter()
+                .map(|entry| (entry.l1_table_offset, entry.l1_size)),
+        );
+        for (offset, size) in l1_tables {
+            reference(offset, image.clusters_for(u64::from(size) * 8));
+            for l1_entry in image.read_l1(offset, size).unwrap() {
+                let l2_offset = l1_entry & L1E_OFFSET_MASK;
+                if l2_offset == 0 {
+                    continue;
+                }
+                reference(l2_offset, 1);
+                for l2_entry in image.read_l2(l2_offset).unwrap() {
+                    let range = image.data_clusters(l2_entry);
+                    reference(range.start * CLUSTER, range.end - range.start);
+                }
+            }
+        }
+
+        if let Some(bitmaps) = header.bitmaps {
+            reference(
+                bitmaps.directory_offset,
+                bitmaps.directory_size.div_ceil(CLUSTER),
+            );
+            for entry in image.directory().unwrap() {
+                reference(
+                    entry.table_offset,
+                    image.clusters_for(u64::from(entry.table_size) * 8),
+                );
+                for table_entry in image.read_table(&entry).unwrap() {
+                    if table_entry & BME_TABLE_OFFSET_MASK != 0 {
+                        reference(table_entry & BME_TABLE_OFFSET_MASK, 1);
+                    }
+                }
+            }
+        }
+
+        let clusters = image.file.metadata().unwrap().len().div_ceil(CLUSTER);
+        for cluster in 0..clusters {
+            assert_eq!(
+                image.refcount(cluster).unwrap(),
+                expected.get(&cluster).copied().unwrap_or(0),
+                "refcount of cluster {}",
+                cluster
+            );
+        }
+
+        for l1_entry in image
+            .read_l1(header.l1_table_offset, header.l1_size)
+            .unwrap()
+        {
+            let l2_offset = l1_entry & L1E_OFFSET_MASK;
+            if l2_offset == 0 {
+                continue;
+            }
+            let copied = image.refcount(l2_offset / CLUSTER).unwrap() == 1;
+            assert_eq!(l1_entry & OFLAG_COPIED != 0, copied);
+            for l2_entry in image.read_l2(l2_offset).unwrap() {
+                let offset = l2_entry & L2E_OFFSET_MASK;
+                if offset != 0 {
+                    let copied = image.refcount(offset / CLUSTER).unwrap() == 1;
+                    assert_eq!(l2_entry & OFLAG_COPIED != 0, copied);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_create_and_list_snapshots() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("disk.qcow2");
+        create_image(&path, 1 << 30, None);
+
+        let mut image = Qcow2Image::open_rw(&path).unwrap();
+        assert!(image.snapshots().unwrap().is_empty());
+        write_guest(&mut image, 0, 0xaa);
+        write_guest(&mut image, 1, 0xbb);
+        check_image(&image);
+
+        let info = image.create_snapshot("base").unwrap();
+        assert_eq!(info.id, "1");
+        assert_eq!(info.disk_size, 1 << 30);
+        assert_eq!(info.vm_state_size, 0);
+        check_image(&image);
+        assert!(image.create_snapshot("base").is_err());
+        assert!(image.create_snapshot("").is_err());
+        assert_eq!(image.create_snapshot("later").unwrap().id, "2");
+        check_image(&image);
+        drop(image);
+
+        let image = Qcow2Image::open(&path).unwrap();
+        let names: Vec<_> = image
+            .snapshots()
+            .unwrap()
+            .into_iter()
+            .map(|snapshot| snapshot.name)
+            .collect();
+        assert_eq!(names, ["base", "later"]);
+        assert_eq!(read_guest(&image, 0), 0xaa);
+    }
+
+    #[test]
+    fn test_revert_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("disk.qcow2");
+        create_image(&path, 1 << 30, None);
+
+        let mut image = Qcow2Image::open_rw(&path).unwrap();
+        write_guest(&mut image, 0, 0xaa);
+        image.create_snapshot("before").unwrap();
+        write_guest(&mut image, 0, 0xcc);
+        write_guest(&mut image, L2_ENTRIES + 5, 0xdd);
+        check_image(&image);
+        assert_eq!(read_guest(&image, 0), 0xcc);
+
+        image.revert_snapshot("before").unwrap();
+        check_image(&image);
+        assert_eq!(read_guest(&image, 0), 0xaa);
+        assert_eq!(read_guest(&image, L2_ENTRIES + 5), 0);
+
+        // Writes after the revert leave the snapshot alone
+        write_guest(&mut image, 0, 0xee);
+        image.revert_snapshot("1").unwrap();
+        check_image(&image);
+        assert_eq!(read_guest(&image, 0), 0xaa);
+        assert_eq!(image.snapshots().unwrap().len(), 1);
+        assert!(matches!(
+            image.revert_snapshot("after"),
+            Err(Error::NotFound(_))
+        ));
+    }
+
+    #[test]
+    fn test_delete_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("disk.qcow2");
+        create_image(&path, 1 << 30, None);
+
+        let mut image = Qcow2Image::open_rw(&path).unwrap();
+        write_guest(&mut image, 0, 0xaa);
+        image.create_snapshot("first").unwrap();
+        write_guest(&mut image, 0, 0xbb);
+        image.create_snapshot("second").unwrap();
+        write_guest(&mut image, 0, 0xcc);
+
+        image.delete_snapshot("first").unwrap();
+        check_image(&image);
+        assert_eq!(image.snapshots().unwrap()[0].name, "second");
+        assert_eq!(image.create_snapshot("third").unwrap().id, "3");
+
+        image.delete_snapshot("second").unwrap();
+        image.delete_snapshot("third").unwrap();
+        check_image(&image);
+        assert_eq!(image.header.nb_snapshots, 0);
+        assert_eq!(image.header.snapshots_offset, 0);
+        assert_eq!(read_guest(&image, 0), 0xcc);
+        assert!(image.delete_snapshot("first").is_err());
+    }
+
+    #[test]
+    fn test_revert_marks_bitmaps_dirty() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("disk.qcow2");
+        create_image(&path, 1 << 30, None);
+
+        let mut image = Qcow2Image::open_rw(&path).unwrap();
+        image.create_snapshot("clean").unwrap();
+        image.add_bitmap("backup", DEFAULT_GRANULARITY).unwrap();
+        assert!(image.changed_blocks("backup").unwrap().extents.is_empty());
+
+        image.revert_snapshot("clean").unwrap();
+        check_image(&image);
+        assert_eq!(
+            image.changed_blocks("backup").unwrap().changed_bytes,
+            1 << 30
+        );
+    }
+
+    #[test]
+    fn test_snapshot_table_roundtrip() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("overlay.qcow2");
+        create_image(&path, 1 << 30, Some("base.qcow2"));
+
+        let mut image = Qcow2Image::open_rw(&path).unwrap();
+        image.create_snapshot("nightly").unwrap();
+        image.add_bitmap("backup", DEFAULT_GRANULARITY).unwrap();
+        drop(image);
+
+        // The header keeps the bitmaps and the backing file alongside
+        let image = Qcow2Image::open(&path).unwrap();
+        let snapshots = image.snapshots().unwrap();
+        assert_eq!(snapshots.len(), 1);
+        assert_eq!(snapshots[0].name, "nightly");
+        assert_eq!(image.bitmaps().unwrap().len(), 1);
+        assert_eq!(image.header.backing_file_size, 10);
+        check_image(&image);
+    }
+}
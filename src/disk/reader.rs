@@ -2,8 +2,16 @@
 //! Disk image reader
 //!
 //! Pure Rust implementation for reading disk images (raw, qcow2, etc.)
+//!
+//! VHDX, VMDK and qcow2 images are read through [`VhdxImage`],
+//! [`VmdkReader`] and [`Qcow2Reader`], so offsets are guest disk offsets;
+//! other formats are read as the file is.
 
 use crate::core::{DiskFormat, Error, Result};
+use crate::disk::allocation::{self, Extent};
+use crate::disk::qcow2::{Qcow2Image, Qcow2Reader};
+use crate::disk::vhdx::{self, VhdxImage};
+use crate::disk::vmdk::{VmdkImage, VmdkReader, DESCRIPTOR_SIGNATURE};
 use std::fs::File;
 use std::io::{Read, Seek, SeekFrom};
 use std::path::Path;
@@ -13,6 +21,40 @@ pub struct DiskReader {
     file: File,
     format: DiskFormat,
     size: u64,
+    guest: Option<GuestData>,
+}
+
+/// Image formats whose guest data is mapped here rather than by QEMU
+enum GuestData {
+    Vhdx(VhdxImage),
+    Vmdk(VmdkReader),
+    Qcow2(Qcow2Reader),
+}
+
+impl GuestData {
+    fn size(&self) -> u64 {
+        match self {
+            GuestData::Vhdx(image) => image.virtual_size,
+            GuestData::Vmdk(reader) => reader.capacity,
+            GuestData::Qcow2(reader) => reader.virtual_size,
+        }
+    }
+
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        match self {
+            GuestData::Vhdx(image) => image.read_at(offset, buf),
+            GuestData::Vmdk(reader) => reader.read_at(offset, buf),
+            GuestData::Qcow2(reader) => reader.read_at(offset, buf),
+        }
+    }
+
+    fn allocation(&self) -> Result<Vec<Extent>> {
+        match self {
+            GuestData::Vhdx(image) => Ok(image.allocation()),
+            GuestData::Vmdk(reader) => reader.allocation(),
+            GuestData::Qcow2(reader) => reader.allocation(),
+        }
+    }
 }
 
 impl DiskReader {
@@ -63,7 +105,39 @@ impl DiskReader {
         use std::io::{Seek, SeekFrom};
         file.seek(SeekFrom::Start(0)).map_err(Error::Io)?;
 
-        Ok(Self { file, format, size })
+        // VHDX blocks, VMDK grains and qcow2 clusters are scattered through
+        // the file (or several files); read them via the BAT, grain tables
+        // or L2 tables
+        let guest = match format {
+            DiskFormat::Vhdx => Some(GuestData::Vhdx(VhdxImage::open(path_ref)?)),
+            DiskFormat::Vmdk => match VmdkImage::open(path_ref).and_then(|vmdk| vmdk.reader()) {
+                Ok(reader) => Some(GuestData::Vmdk(reader)),
+                // seSparse and the like: readable through NBD only
+                Err(Error::Unsupported(reason)) => {
+                    log::warn!("{}: {}", path_ref.display(), reason);
+                    None
+                }
+                Err(e) => return Err(e),
+            },
+            DiskFormat::Qcow2 => match Qcow2Image::open(path_ref).and_then(|image| image.reader()) {
+                Ok(reader) => Some(GuestData::Qcow2(reader)),
+                // Encrypted images and the like: readable through NBD only
+                Err(Error::Unsupported(reason)) => {
+                    log::warn!("{}: {}", path_ref.display(), reason);
+                    None
+                }
+                Err(e) => return Err(e),
+            },
+            _ => None,
+        };
+        let size = guest.as_ref().map_or(size, GuestData::size);
+
+        Ok(Self {
+            file,
+            format,
+            size,
+            guest,
+        })
     }
 
     /// Check if path is a block device
@@ -80,7 +154,7 @@ impl DiskReader {
 
     /// Detect disk image format from magic bytes
     fn detect_format(file: &mut File) -> Result<DiskFormat> {
-        let mut magic = [0u8; 4];
+        let mut magic = [0u8; 32];
         file.seek(SeekFrom::Start(0)).map_err(Error::Io)?;
 
         // Use read() instead of read_exact() for block devices
@@ -93,7 +167,7 @@ impl DiskReader {
         }
 
         // QCOW2 magic: "QFI\xfb"
-        if &magic == b"QFI\xfb" {
+        if &magic[0..4] == b"QFI\xfb" {
             return Ok(DiskFormat::Qcow2);
         }
 
@@ -103,6 +177,16 @@ impl DiskReader {
             return Ok(DiskFormat::Vmdk);
         }
 
+        // VMDK descriptor file (split, flat and snapshot disks)
+        if magic[..bytes_read].starts_with(DESCRIPTOR_SIGNATURE) {
+            return Ok(DiskFormat::Vmdk);
+        }
+
+        // VHDX file type identifier
+        if magic.starts_with(vhdx::FILE_SIGNATURE) {
+            return Ok(DiskFormat::Vhdx);
+        }
+
         // VHD magic at end (512 bytes from end) "conectix"
         // VDI magic "<<< "
 
@@ -112,12 +196,23 @@ impl DiskReader {
 
     /// Read bytes at offset
     pub fn read_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<usize> {
+        if let Some(guest) = &self.guest {
+            let len = buf.len().min(self.size.saturating_sub(offset) as usize);
+            guest.read_at(offset, &mut buf[..len])?;
+            return Ok(len);
+        }
         self.file
             .seek(SeekFrom::Start(offset))
             .map_err(Error::Io)?;
         self.file.read(buf).map_err(Error::Io)
     }
 
+    /// Whether reads return guest data: raw images and block devices, and
+    /// the VHDX, VMDK and qcow2 layouts mapped here
+    pub fn reads_guest_data(&self) -> bool {
+        self.guest.is_some() || self.format == DiskFormat::Raw
+    }
+
     /// Get disk format
     pub fn format(&self) -> &DiskFormat {
         &self.format
@@ -128,8 +223,45 @@ impl DiskReader {
         self.size
     }
 
+    /// Logical and physical sector size, for block devices and VHDX only
+    ///
+    /// Other image files have no sector size of their own; the hypervisor
+    /// picks one when presenting them to a guest.
+    pub fn sector_sizes(&self) -> Option<(u32, u32)> {
+        if let Some(GuestData::Vhdx(image)) = &self.guest {
+            return Some((image.logical_sector_size, image.physical_sector_size));
+        }
+        #[cfg(target_os = "linux")]
+        {
+            use std::os::unix::fs::FileTypeExt;
+            use std::os::unix::io::AsRawFd;
+            const BLKSSZGET: libc::c_ulong = 0x1268;
+            const BLKPBSZGET: libc::c_ulong = 0x127b;
+
+            if !self.file.metadata().ok()?.file_type().is_block_device() {
+                return None;
+            }
+            let mut logical: libc::c_int = 0;
+            let mut physical: libc::c_uint = 0;
+            let fd = self.file.as_raw_fd();
+            let ok = unsafe {
+                libc::ioctl(fd, BLKSSZGET as _, &mut logical as *mut libc::c_int) == 0
+                    && libc::ioctl(fd, BLKPBSZGET as _, &mut physical as *mut libc::c_uint) == 0
+            };
+            ok.then_some((logical as u32, physical))
+        }
+        #[cfg(not(target_os = "linux"))]
+        {
+            None
+        }
+    }
+
     /// Read exact bytes at offset
     pub fn read_exact_at(&mut self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        if let Some(guest) = &self.guest {
+            return guest.read_at(offset, buf);
+        }
+
         self.file
             .seek(SeekFrom::Start(offset))
             .map_err(Error::Io)?;
@@ -156,6 +288,33 @@ impl DiskReader {
 
         Ok(())
     }
+
+    /// Read exact bytes at offset without moving the file position, so
+    /// several threads can share the reader
+    pub fn pread_exact(&self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        use std::os::unix::fs::FileExt;
+
+        match &self.guest {
+            Some(guest) => guest.read_at(offset, buf),
+            None => self.file.read_exact_at(buf, offset).map_err(Error::Io),
+        }
+    }
+
+    /// Ranges that may hold data; the rest reads as zeros
+    ///
+    /// From the image metadata when the guest data is mapped here, from the
+    /// filesystem (`SEEK_DATA`) otherwise.
+    pub fn allocation(&self) -> Result<Vec<Extent>> {
+        match &self.guest {
+            Some(guest) => guest.allocation(),
+            None => allocation::file_extents(&self.file, self.size),
+        }
+    }
+
+    /// The underlying file when it holds the guest data as is
+    pub fn raw_file(&self) -> Option<&File> {
+        (self.guest.is_none() && self.format == DiskFormat::Raw).then_some(&self.file)
+    }
 }
 
 #[cfg(test)]
@@ -0,0 +1,1336 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! VHDX reader
+//!
+//! VHDX is the format Hyper-V creates and exports. The virtual disk is cut
+//! into blocks of 1-256 MiB located through the block allocation table
+//! (BAT); a metadata region holds the disk size, block size, sector sizes
+//! and, for differencing disks, where to find the parent.
+//!
+//! Hyper-V updates the BAT and metadata through a write-ahead log, and a
+//! VM that was not shut down cleanly leaves updates in the log that the
+//! file itself doesn't have yet. QEMU refuses to open such an image
+//! read-only. [`VhdxImage::open`] replays the log in memory instead, so the
+//! image is read as Hyper-V would see it without being modified.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use guestkit::disk::VhdxImage;
+//!
+//! let vhdx = VhdxImage::open("/srv/import/dc01.vhdx")?;
+//! let mut mbr = [0u8; 512];
+//! vhdx.read_at(0, &mut mbr)?;
+//! println!("{} bytes, {} byte blocks", vhdx.virtual_size, vhdx.block_size);
+//! # Ok::<(), guestkit::Error>(())
+//! ```
+
+use crate::core::{Error, Result};
+use crate::disk::allocation::{push_extent, Extent};
+use std::fmt;
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use uuid::{uuid, Uuid};
+
+const KIB: u64 = 1024;
+const MIB: u64 = 1024 * KIB;
+
+/// File type identifier at offset 0
+pub(crate) const FILE_SIGNATURE: &[u8; 8] = b"vhdxfile";
+
+const HEADER_SIGNATURE: &[u8; 4] = b"head";
+const HEADER_OFFSETS: [u64; 2] = [64 * KIB, 128 * KIB];
+const HEADER_SIZE: usize = 4096;
+
+const REGION_SIGNATURE: &[u8; 4] = b"regi";
+const REGION_TABLE_OFFSETS: [u64; 2] = [192 * KIB, 256 * KIB];
+const REGION_TABLE_SIZE: usize = 64 * 1024;
+const MAX_TABLE_ENTRIES: usize = 2047;
+
+const METADATA_SIGNATURE: &[u8; 8] = b"metadata";
+const METADATA_TABLE_SIZE: usize = 64 * 1024;
+
+const BAT_REGION: Uuid = uuid!("2dc27766-f623-4200-9d64-115e9bfd4a08");
+const METADATA_REGION: Uuid = uuid!("8b7ca206-4790-4b9a-b8fe-575f050f886e");
+
+const FILE_PARAMETERS: Uuid = uuid!("caa16737-fa36-4d43-b3b6-33f0aa44e76b");
+const VIRTUAL_DISK_SIZE: Uuid = uuid!("2fa54224-cd1b-4876-b211-5dbed83bf4b8");
+const VIRTUAL_DISK_ID: Uuid = uuid!("beca12ab-b2e6-4523-93ef-c309e000c746");
+const LOGICAL_SECTOR_SIZE: Uuid = uuid!("8141bf1d-a96f-4709-ba47-f233a8faab5f");
+const PHYSICAL_SECTOR_SIZE: Uuid = uuid!("cda348c7-445d-4471-9cc9-e9885251c556");
+const PARENT_LOCATOR: Uuid = uuid!("a8d35f2d-b30b-454d-abf7-d3d84834ab0c");
+/// The only parent locator type: the parent is a VHDX too
+const VHDX_PARENT_LOCATOR: Uuid = uuid!("b04aefb7-d19e-4a81-b789-25b8e9445913");
+
+/// File parameters flags
+const HAS_PARENT: u32 = 1 << 1;
+/// Metadata entry flags
+const METADATA_IS_REQUIRED: u32 = 1 << 2;
+
+/// BAT entry states; the file offset is in the upper 44 bits, in MiB
+const BAT_STATE_MASK: u64 = 7;
+const PAYLOAD_NOT_PRESENT: u64 = 0;
+const PAYLOAD_FULLY_PRESENT: u64 = 6;
+const PAYLOAD_PARTIALLY_PRESENT: u64 = 7;
+const SECTOR_BITMAP_PRESENT: u64 = 6;
+
+/// Log entries are made of 4 KiB sectors
+const LOG_ENTRY_SIGNATURE: &[u8; 4] = b"loge";
+const LOG_ZERO_SIGNATURE: &[u8; 4] = b"zero";
+const LOG_DESC_SIGNATURE: &[u8; 4] = b"desc";
+const LOG_DATA_SIGNATURE: &[u8; 4] = b"data";
+const LOG_SECTOR: u64 = 4096;
+const LOG_HEADER_SIZE: usize = 64;
+const LOG_DESCRIPTOR_SIZE: usize = 32;
+
+const MIN_BLOCK_SIZE: u32 = MIB as u32;
+const MAX_BLOCK_SIZE: u32 = 256 * MIB as u32;
+const MAX_VIRTUAL_SIZE: u64 = 64 * 1024 * 1024 * MIB;
+/// Sectors described by one sector bitmap block
+const SECTORS_PER_CHUNK: u64 = 1 << 23;
+
+/// Longest differencing chain followed before assuming a loop
+const MAX_CHAIN_LENGTH: usize = 64;
+
+/// Whether `path` starts with the VHDX file type identifier
+pub fn is_vhdx<P: AsRef<Path>>(path: P) -> bool {
+    let mut signature = [0u8; 8];
+    File::open(path)
+        .and_then(|file| file.read_exact_at(&mut signature, 0))
+        .is_ok()
+        && &signature == FILE_SIGNATURE
+}
+
+/// An open VHDX image, dynamic or differencing
+pub struct VhdxImage {
+    pub path: PathBuf,
+    /// Virtual disk size in bytes
+    pub virtual_size: u64,
+    pub block_size: u32,
+    pub logical_sector_size: u32,
+    pub physical_sector_size: u32,
+    /// Parent of a differencing disk as recorded in its parent locator,
+    /// e.g. `.\base.vhdx`
+    pub parent: Option<String>,
+    /// The log held updates missing from the file (the VM wasn't shut down
+    /// cleanly); they were applied in memory
+    pub log_replayed: bool,
+    file: File,
+    /// Length of the file once the log is replayed
+    file_size: u64,
+    log_writes: Vec<LogWrite>,
+    bat: Vec<u64>,
+    chunk_ratio: u64,
+    parent_image: Option<Box<VhdxImage>>,
+}
+
+impl fmt::Debug for VhdxImage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("VhdxImage")
+            .field("path", &self.path)
+            .field("virtual_size", &self.virtual_size)
+            .field("block_size", &self.block_size)
+            .field("logical_sector_size", &self.logical_sector_size)
+            .field("physical_sector_size", &self.physical_sector_size)
+            .field("parent", &self.parent)
+            .field("log_replayed", &self.log_replayed)
+            .finish_non_exhaustive()
+    }
+}
+
+#[derive(Debug, Clone)]
+struct Header {
+    sequence: u64,
+    data_write_guid: Uuid,
+    log_guid: Uuid,
+    log_version: u16,
+    version: u16,
+    log_length: u32,
+    log_offset: u64,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct Region {
+    offset: u64,
+    length: u64,
+}
+
+/// One update from the log, applied over the file contents when reading
+#[derive(Debug, Clone)]
+enum LogWrite {
+    Data { offset: u64, data: Vec<u8> },
+    Zero { offset: u64, length: u64 },
+}
+
+#[derive(Debug, Clone)]
+struct LogEntry {
+    /// Offset within the log
+    offset: u64,
+    length: u64,
+    tail: u64,
+    sequence: u64,
+    last_file_offset: u64,
+    writes: Vec<LogWrite>,
+}
+
+/// Values read from the metadata region
+#[derive(Debug, Default)]
+struct Metadata {
+    block_size: Option<u32>,
+    has_parent: bool,
+    virtual_size: Option<u64>,
+    logical_sector_size: Option<u32>,
+    physical_sector_size: Option<u32>,
+    parent_locator: Vec<(String, String)>,
+}
+
+impl VhdxImage {
+    /// Open a VHDX and, for a differencing disk, its parents
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::open_layer(path.as_ref(), 0)
+    }
+
+    fn open_layer(path: &Path, depth: usize) -> Result<Self> {
+        let file = File::open(path).map_err(Error::Io)?;
+        let len = file.metadata().map_err(Error::Io)?.len();
+
+        let mut signature = [0u8; 8];
+        if file.read_exact_at(&mut signature, 0).is_err() || &signature != FILE_SIGNATURE {
+            return Err(Error::InvalidFormat(format!(
+                "{} is not a VHDX",
+                path.display()
+            )));
+        }
+
+        let header = read_header(path, &file)?;
+        if header.version != 1 {
+            return Err(Error::Unsupported(format!(
+                "{}: VHDX version {}",
+                path.display(),
+                header.version
+            )));
+        }
+
+        let mut image = Self {
+            path: path.to_path_buf(),
+            virtual_size: 0,
+            block_size: 0,
+            logical_sector_size: 0,
+            physical_sector_size: 0,
+            parent: None,
+            log_replayed: false,
+            file,
+            file_size: len,
+            log_writes: Vec::new(),
+            bat: Vec::new(),
+            chunk_ratio: 0,
+            parent_image: None,
+        };
+        image.replay_log(&header)?;
+
+        let (bat_region, metadata_region) = image.read_region_table()?;
+        let metadata = image.read_metadata(metadata_region)?;
+        image.apply_metadata(&metadata)?;
+        image.read_bat(bat_region, metadata.has_parent)?;
+
+        if metadata.has_parent {
+            image.open_parent(&metadata.parent_locator, depth)?;
+        }
+        Ok(image)
+    }
+
+    /// Whether blocks missing from this image are read from a parent
+    pub fn is_differencing(&self) -> bool {
+        self.parent_image.is_some()
+    }
+
+    /// The parent of a differencing disk
+    pub fn parent_image(&self) -> Option<&VhdxImage> {
+        self.parent_image.as_deref()
+    }
+
+    /// Read guest data; the whole range must lie within the virtual disk
+    pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        let in_range = offset
+            .checked_add(buf.len() as u64)
+            .is_some_and(|end| end <= self.virtual_size);
+        if !in_range {
+            return Err(Error::InputValidation(format!(
+                "Read of {} bytes at {} is beyond the {} byte disk",
+                buf.len(),
+                offset,
+                self.virtual_size
+            )));
+        }
+
+        let block_size = u64::from(self.block_size);
+        let mut done = 0;
+        while done < buf.len() {
+            let position = offset + done as u64;
+            let within = position % block_size;
+            let length = (buf.len() - done).min((block_size - within) as usize);
+            self.read_block(position / block_size, within, &mut buf[done..done + length])?;
+            done += length;
+        }
+        Ok(())
+    }
+
+    /// Write the guest data to a sparse raw image, skipping blocks no
+    /// layer of the chain has allocated
+    pub fn write_raw<P: AsRef<Path>>(&self, dest: P) -> Result<()> {
+        let dest = dest.as_ref();
+        let out = File::create(dest).map_err(Error::Io)?;
+        out.set_len(self.virtual_size).map_err(Error::Io)?;
+
+        let block_size = u64::from(self.block_size);
+        let mut buf = vec![0u8; MIB as usize];
+        for block in 0..self.virtual_size.div_ceil(block_size) {
+            if !self.is_allocated(block) {
+                continue;
+            }
+            let start = block * block_size;
+            let end = (start + block_size).min(self.virtual_size);
+            let mut offset = start;
+            while offset < end {
+                let chunk = &mut buf[..(end - offset).min(MIB) as usize];
+                self.read_at(offset, chunk)?;
+                if chunk.iter().any(|&byte| byte != 0) {
+                    out.write_all_at(chunk, offset).map_err(Error::Io)?;
+                }
+                offset += chunk.len() as u64;
+            }
+        }
+        out.sync_all().map_err(Error::Io)
+    }
+
+    /// Guest ranges some layer of the chain has data for, a block at a time
+    pub fn allocation(&self) -> Vec<Extent> {
+        let block_size = u64::from(self.block_size);
+        let mut extents = Vec::new();
+        for block in 0..self.virtual_size.div_ceil(block_size) {
+            if self.is_allocated(block) {
+                let start = block * block_size;
+                push_extent(
+                    &mut extents,
+                    start,
+                    block_size.min(self.virtual_size - start),
+                );
+            }
+        }
+        extents
+    }
+
+    fn bat_entry(&self, block: u64) -> u64 {
+        self.bat[(block + block / self.chunk_ratio) as usize]
+    }
+
+    /// Whether this image or a parent holds data for `block`
+    fn is_allocated(&self, block: u64) -> bool {
+        match self.bat_entry(block) & BAT_STATE_MASK {
+            PAYLOAD_FULLY_PRESENT | PAYLOAD_PARTIALLY_PRESENT => true,
+            PAYLOAD_NOT_PRESENT => self
+                .parent_image
+                .as_ref()
+                .is_some_and(|parent| parent.is_allocated(block)),
+            _ => false,
+        }
+    }
+
+    fn read_block(&self, block: u64, within: u64, buf: &mut [u8]) -> Result<()> {
+        let entry = self.bat_entry(block);
+        let block_offset = entry & !(MIB - 1);
+        let position = block * u64::from(self.block_size) + within;
+
+        match (entry & BAT_STATE_MASK, &self.parent_image) {
+            (PAYLOAD_FULLY_PRESENT, _) => self.read_file(block_offset + within, buf),
+            (PAYLOAD_PARTIALLY_PRESENT, Some(parent)) => {
+                self.read_partial(block, block_offset, within, buf, parent)
+            }
+            (PAYLOAD_PARTIALLY_PRESENT, None) => Err(Error::InvalidFormat(format!(
+                "{}: partially present block {} in a disk without a parent",
+                self.path.display(),
+                block
+            ))),
+            (PAYLOAD_NOT_PRESENT, Some(parent)) => parent.read_at(position, buf),
+            // Not present in a dynamic disk, undefined, zero or unmapped
+            _ => {
+                buf.fill(0);
+                Ok(())
+            }
+        }
+    }
+
+    /// Block of a differencing disk holding some of its sectors: the
+    /// sector bitmap tells which ones, the others come from the parent
+    fn read_partial(
+        &self,
+        block: u64,
+        block_offset: u64,
+        within: u64,
+        buf: &mut [u8],
+        parent: &VhdxImage,
+    ) -> Result<()> {
+        let chunk = block / self.chunk_ratio;
+        let bitmap_entry = self.bat[(chunk * (self.chunk_ratio + 1) + self.chunk_ratio) as usize];
+        if bitmap_entry & BAT_STATE_MASK != SECTOR_BITMAP_PRESENT {
+            return Err(Error::InvalidFormat(format!(
+                "{}: block {} is partially present but has no sector bitmap",
+                self.path.display(),
+                block
+            )));
+        }
+
+        let sector_size = u64::from(self.logical_sector_size);
+        let block_sector = (block % self.chunk_ratio) * (u64::from(self.block_size) / sector_size);
+        let first = within / sector_size;
+        let last = (within + buf.len() as u64 - 1) / sector_size;
+
+        let bits_start = (block_sector + first) / 8;
+        let mut bits = vec![0u8; ((block_sector + last) / 8 - bits_start + 1) as usize];
+        self.read_file((bitmap_entry & !(MIB - 1)) + bits_start, &mut bits)?;
+        let present = |sector: u64| {
+            let bit = block_sector + sector;
+            bits[(bit / 8 - bits_start) as usize] & (1 << (bit % 8)) != 0
+        };
+
+        // Read runs of sectors that come from the same layer
+        let mut sector = first;
+        while sector <= last {
+            let here = present(sector);
+            let mut end = sector + 1;
+            while end <= last && present(end) == here {
+                end += 1;
+            }
+            let from = (sector * sector_size).max(within);
+            let to = (end * sector_size).min(within + buf.len() as u64);
+            let part = &mut buf[(from - within) as usize..(to - within) as usize];
+            if here {
+                self.read_file(block_offset + from, part)?;
+            } else {
+                parent.read_at(block * u64::from(self.block_size) + from, part)?;
+            }
+            sector = end;
+        }
+        Ok(())
+    }
+
+    /// Read the file as it is after replaying the log
+    fn read_file(&self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        let end = offset + buf.len() as u64;
+        if end > self.file_size {
+            return Err(Error::InvalidFormat(format!(
+                "{} is truncated: {} bytes needed, {} present",
+                self.path.display(),
+                end,
+                self.file_size
+            )));
+        }
+
+        // The log may have grown the file past its length on disk
+        let mut done = 0;
+        while done < buf.len() {
+            match self.file.read_at(&mut buf[done..], offset + done as u64) {
+                Ok(0) => {
+                    buf[done..].fill(0);
+                    break;
+                }
+                Ok(n) => done += n,
+                Err(e) if e.kind() == std::io::ErrorKind::Interrupted => {}
+                Err(e) => return Err(Error::Io(e)),
+            }
+        }
+
+        for write in &self.log_writes {
+            let (start, length) = match write {
+                LogWrite::Data { offset, data } => (*offset, data.len() as u64),
+                LogWrite::Zero { offset, length } => (*offset, *length),
+            };
+            let from = start.max(offset);
+            let to = (start + length).min(end);
+            if from >= to {
+                continue;
+            }
+            let target = &mut buf[(from - offset) as usize..(to - offset) as usize];
+            match write {
+                LogWrite::Data { data, .. } => {
+                    target.copy_from_slice(&data[(from - start) as usize..(to - start) as usize])
+                }
+                LogWrite::Zero { .. } => target.fill(0),
+            }
+        }
+        Ok(())
+    }
+
+    /// Find the active sequence of log entries and keep its updates
+    fn replay_log(&mut self, header: &Header) -> Result<()> {
+        if header.log_guid.is_nil() {
+            return Ok(());
+        }
+        if header.log_version != 0 {
+            return Err(Error::Unsupported(format!(
+                "{}: VHDX log version {}",
+                self.path.display(),
+                header.log_version
+            )));
+        }
+        let log = Region {
+            offset: header.log_offset,
+            length: u64::from(header.log_length),
+        };
+        if log.length == 0
+            || !log.length.is_multiple_of(MIB)
+            || !log.offset.is_multiple_of(MIB)
+            || log.offset + log.length > self.file_size
+        {
+            return Err(Error::InvalidFormat(format!(
+                "{}: log region out of bounds",
+                self.path.display()
+            )));
+        }
+        let mut data = vec![0u8; log.length as usize];
+        self.file
+            .read_exact_at(&mut data, log.offset)
+            .map_err(Error::Io)?;
+
+        let Some(sequence) = active_sequence(&data, header.log_guid) else {
+            // Nothing valid was logged: the file is up to date
+            return Ok(());
+        };
+
+        log::info!(
+            "{}: replaying {} VHDX log entries in memory",
+            self.path.display(),
+            sequence.len()
+        );
+        for entry in sequence {
+            self.file_size = self.file_size.max(entry.last_file_offset);
+            self.log_writes.extend(entry.writes);
+        }
+        self.log_replayed = true;
+        Ok(())
+    }
+
+    /// Locate the BAT and metadata regions
+    fn read_region_table(&self) -> Result<(Region, Region)> {
+        let mut table = vec![0u8; REGION_TABLE_SIZE];
+        let mut valid = false;
+        for offset in REGION_TABLE_OFFSETS {
+            self.read_file(offset, &mut table)?;
+            if &table[0..4] == REGION_SIGNATURE && checksum_matches(&table, 4) {
+                valid = true;
+                break;
+            }
+        }
+        if !valid {
+            return Err(Error::InvalidFormat(format!(
+                "{}: no valid region table",
+                self.path.display()
+            )));
+        }
+
+        let count = le_u32(&table, 8) as usize;
+        if count > MAX_TABLE_ENTRIES {
+            return Err(Error::InvalidFormat(format!(
+                "{}: {} regions",
+                self.path.display(),
+                count
+            )));
+        }
+
+        let (mut bat, mut metadata) = (None, None);
+        for index in 0..count {
+            let entry = &table[16 + index * 32..16 + (index + 1) * 32];
+            let guid = read_guid(entry, 0);
+            let region = Region {
+                offset: le_u64(entry, 16),
+                length: u64::from(le_u32(entry, 24)),
+            };
+            let required = le_u32(entry, 28) & 1 != 0;
+
+            if !region.offset.is_multiple_of(MIB) || region.offset + region.length > self.file_size
+            {
+                return Err(Error::InvalidFormat(format!(
+                    "{}: region {} out of bounds",
+                    self.path.display(),
+                    guid
+                )));
+            }
+            match guid {
+                BAT_REGION => bat = Some(region),
+                METADATA_REGION => metadata = Some(region),
+                _ if required => {
+                    return Err(Error::Unsupported(format!(
+                        "{}: unknown required region {}",
+                        self.path.display(),
+                        guid
+                    )))
+                }
+                _ => {}
+            }
+        }
+
+        match (bat, metadata) {
+            (Some(bat), Some(metadata)) => Ok((bat, metadata)),
+            _ => Err(Error::InvalidFormat(format!(
+                "{}: BAT or metadata region missing",
+                self.path.display()
+            ))),
+        }
+    }
+
+    fn read_metadata(&self, region: Region) -> Result<Metadata> {
+        if region.length < METADATA_TABLE_SIZE as u64 {
+            return Err(Error::InvalidFormat(format!(
+                "{}: metadata region too small",
+                self.path.display()
+            )));
+        }
+        let mut table = vec![0u8; METADATA_TABLE_SIZE];
+        self.read_file(region.offset, &mut table)?;
+        if &table[0..8] != METADATA_SIGNATURE {
+            return Err(Error::InvalidFormat(format!(
+                "{}: invalid metadata table",
+                self.path.display()
+            )));
+        }
+
+        let count = usize::from(le_u16(&table, 10));
+        if count > MAX_TABLE_ENTRIES {
+            return Err(Error::InvalidFormat(format!(
+                "{}: {} metadata items",
+                self.path.display(),
+                count
+            )));
+        }
+
+        let mut metadata = Metadata::default();
+        for index in 0..count {
+            let entry = &table[32 + index * 32..32 + (index + 1) * 32];
+            let id = read_guid(entry, 0);
+            let offset = u64::from(le_u32(entry, 16));
+            let length = le_u32(entry, 20) as usize;
+            let required = le_u32(entry, 24) & METADATA_IS_REQUIRED != 0;
+
+            if offset + length as u64 > region.length {
+                return Err(Error::InvalidFormat(format!(
+                    "{}: metadata item {} out of bounds",
+                    self.path.display(),
+                    id
+                )));
+            }
+            let mut item = vec![0u8; length];
+            self.read_file(region.offset + offset, &mut item)?;
+            let too_short = |needed: usize| {
+                (item.len() < needed).then(|| {
+                    Error::InvalidFormat(format!(
+                        "{}: metadata item {} too short",
+                        self.path.display(),
+                        id
+                    ))
+                })
+            };
+
+            match id {
+                FILE_PARAMETERS => {
+                    if let Some(err) = too_short(8) {
+                        return Err(err);
+                    }
+                    metadata.block_size = Some(le_u32(&item, 0));
+                    metadata.has_parent = le_u32(&item, 4) & HAS_PARENT != 0;
+                }
+                VIRTUAL_DISK_SIZE => {
+                    if let Some(err) = too_short(8) {
+                        return Err(err);
+                    }
+                    metadata.virtual_size = Some(le_u64(&item, 0));
+                }
+                LOGICAL_SECTOR_SIZE | PHYSICAL_SECTOR_SIZE => {
+                    if let Some(err) = too_short(4) {
+                        return Err(err);
+                    }
+                    let size = Some(le_u32(&item, 0));
+                    if id == LOGICAL_SECTOR_SIZE {
+                        metadata.logical_sector_size = size;
+                    } else {
+                        metadata.physical_sector_size = size;
+                    }
+                }
+                PARENT_LOCATOR => {
+                    metadata.parent_locator = parse_parent_locator(&item).ok_or_else(|| {
+                        Error::InvalidFormat(format!(
+                            "{}: invalid parent locator",
+                            self.path.display()
+                        ))
+                    })?;
+                }
+                VIRTUAL_DISK_ID => {}
+                _ if required => {
+                    return Err(Error::Unsupported(format!(
+                        "{}: unknown required metadata item {}",
+                        self.path.display(),
+                        id
+                    )))
+                }
+                _ => {}
+            }
+        }
+        Ok(metadata)
+    }
+
+    fn apply_metadata(&mut self, metadata: &Metadata) -> Result<()> {
+        let invalid =
+            |what: &str| Error::InvalidFormat(format!("{}: invalid {}", self.path.display(), what));
+
+        let block_size = metadata
+            .block_size
+            .filter(|size| {
+                size.is_power_of_two() && (MIN_BLOCK_SIZE..=MAX_BLOCK_SIZE).contains(size)
+            })
+            .ok_or_else(|| invalid("block size"))?;
+        let logical_sector_size = metadata
+            .logical_sector_size
+            .filter(|size| matches!(size, 512 | 4096))
+            .ok_or_else(|| invalid("logical sector size"))?;
+        let physical_sector_size = metadata
+            .physical_sector_size
+            .filter(|size| matches!(size, 512 | 4096))
+            .ok_or_else(|| invalid("physical sector size"))?;
+        let virtual_size = metadata
+            .virtual_size
+            .filter(|size| {
+                *size <= MAX_VIRTUAL_SIZE && size.is_multiple_of(u64::from(logical_sector_size))
+            })
+            .ok_or_else(|| invalid("virtual disk size"))?;
+
+        self.block_size = block_size;
+        self.logical_sector_size = logical_sector_size;
+        self.physical_sector_size = physical_sector_size;
+        self.virtual_size = virtual_size;
+        self.chunk_ratio =
+            SECTORS_PER_CHUNK * u64::from(logical_sector_size) / u64::from(block_size);
+        Ok(())
+    }
+
+    fn read_bat(&mut self, region: Region, has_parent: bool) -> Result<()> {
+        let blocks = self.virtual_size.div_ceil(u64::from(self.block_size));
+        // Differencing disks have a sector bitmap entry after every chunk,
+        // dynamic ones only between chunks
+        let entries = if has_parent {
+            blocks.div_ceil(self.chunk_ratio) * (self.chunk_ratio + 1)
+        } else {
+            blocks + blocks.saturating_sub(1) / self.chunk_ratio
+        };
+        if entries * 8 > region.length {
+            return Err(Error::InvalidFormat(format!(
+                "{}: BAT region holds fewer than {} entries",
+                self.path.display(),
+                entries
+            )));
+        }
+
+        let mut bat = vec![0u8; (entries * 8) as usize];
+        self.read_file(region.offset, &mut bat)?;
+        self.bat = bat.chunks_exact(8).map(|entry| le_u64(entry, 0)).collect();
+        Ok(())
+    }
+
+    /// Find the parent named by the locator next to this image and check
+    /// it is the one the differencing disk was created from
+    fn open_parent(&mut self, locator: &[(String, String)], depth: usize) -> Result<()> {
+        if depth + 1 >= MAX_CHAIN_LENGTH {
+            return Err(Error::InvalidFormat(format!(
+                "{}: differencing chain longer than {} disks",
+                self.path.display(),
+                MAX_CHAIN_LENGTH
+            )));
+        }
+        let value = |key: &str| {
+            locator
+                .iter()
+                .find(|(name, _)| name == key)
+                .map(|(_, value)| value.as_str())
+        };
+        let linkage = value("parent_linkage")
+            .and_then(|guid| Uuid::parse_str(guid).ok())
+            .ok_or_else(|| {
+                Error::InvalidFormat(format!(
+                    "{}: parent locator has no parent_linkage",
+                    self.path.display()
+                ))
+            })?;
+
+        // Windows paths: try the relative one, then the absolute one's
+        // file name, both from this image's directory
+        let dir = self.path.parent().unwrap_or_else(|| Path::new("."));
+        let relative = value("relative_path").map(|path| dir.join(path.replace('\\', "/")));
+        let absolute = value("absolute_win32_path")
+            .and_then(|path| path.rsplit('\\').next())
+            .map(|name| dir.join(name));
+        self.parent = value("relative_path")
+            .or(value("absolute_win32_path"))
+            .map(String::from);
+
+        let path = relative
+            .into_iter()
+            .chain(absolute)
+            .find(|path| path.exists())
+            .ok_or_else(|| {
+                Error::NotFound(format!(
+                    "Parent disk {} of {} is missing; copy it alongside",
+                    self.parent.as_deref().unwrap_or("(unnamed)"),
+                    self.path.display()
+                ))
+            })?;
+
+        let parent = Self::open_layer(&path, depth + 1)?;
+        let header = read_header(&path, &parent.file)?;
+        if header.data_write_guid != linkage {
+            return Err(Error::InvalidFormat(format!(
+                "{} was modified after {} was created from it",
+                path.display(),
+                self.path.display()
+            )));
+        }
+        if parent.virtual_size != self.virtual_size {
+            return Err(Error::InvalidFormat(format!(
+                "{} and its parent {} differ in size",
+                self.path.display(),
+                path.display()
+            )));
+        }
+        self.parent_image = Some(Box::new(parent));
+        Ok(())
+    }
+}
+
+/// The current header: the valid one with the higher sequence number
+fn read_header(path: &Path, file: &File) -> Result<Header> {
+    let mut best: Option<Header> = None;
+    for offset in HEADER_OFFSETS {
+        let mut buf = [0u8; HEADER_SIZE];
+        if file.read_exact_at(&mut buf, offset).is_err()
+            || &buf[0..4] != HEADER_SIGNATURE
+            || !checksum_matches(&buf, 4)
+        {
+            continue;
+        }
+        let header = Header {
+            sequence: le_u64(&buf, 8),
+            data_write_guid: read_guid(&buf, 32),
+            log_guid: read_guid(&buf, 48),
+            log_version: le_u16(&buf, 64),
+            version: le_u16(&buf, 66),
+            log_length: le_u32(&buf, 68),
+            log_offset: le_u64(&buf, 72),
+        };
+        if best
+            .as_ref()
+            .is_none_or(|best| header.sequence > best.sequence)
+        {
+            best = Some(header);
+        }
+    }
+    best.ok_or_else(|| Error::InvalidFormat(format!("{}: no valid VHDX header", path.display())))
+}
+
+/// The entries to replay, oldest first: the longest run of consecutive
+/// sequence numbers ending in the newest entry whose tail is in the run
+fn active_sequence(log: &[u8], log_guid: Uuid) -> Option<Vec<LogEntry>> {
+    let mut best: Option<Vec<LogEntry>> = None;
+    let sectors = log.len() as u64 / LOG_SECTOR;
+
+    for start in 0..sectors {
+        let Some(first) = parse_log_entry(log, start * LOG_SECTOR, log_guid) else {
+            continue;
+        };
+        let mut run = vec![first];
+        let mut covered = run[0].length / LOG_SECTOR;
+        loop {
+            let last = run.last().unwrap();
+            let next = (last.offset + last.length) % log.len() as u64;
+            match parse_log_entry(log, next, log_guid) {
+                Some(entry)
+                    if entry.sequence == last.sequence + 1
+                        && covered + entry.length / LOG_SECTOR <= sectors =>
+                {
+                    covered += entry.length / LOG_SECTOR;
+                    run.push(entry);
+                }
+                _ => break,
+            }
+        }
+
+        let head = run.last().unwrap();
+        let Some(tail) = run.iter().position(|entry| entry.offset == head.tail) else {
+            continue;
+        };
+        if best
+            .as_ref()
+            .is_none_or(|best| head.sequence > best.last().unwrap().sequence)
+        {
+            best = Some(run.split_off(tail));
+        }
+    }
+    best
+}
+
+/// Parse and verify the log entry at `offset` within the log
+fn parse_log_entry(log: &[u8], offset: u64, log_guid: Uuid) -> Option<LogEntry> {
+    let log_len = log.len() as u64;
+    let sector = |index: u64| {
+        let start = ((offset + index * LOG_SECTOR) % log_len) as usize;
+        &log[start..start + LOG_SECTOR as usize]
+    };
+
+    let header = sector(0);
+    if &header[0..4] != LOG_ENTRY_SIGNATURE {
+        return None;
+    }
+    let length = u64::from(le_u32(header, 8));
+    let tail = u64::from(le_u32(header, 12));
+    let sequence = le_u64(header, 16);
+    let descriptors = le_u32(header, 24) as usize;
+    if length == 0
+        || !length.is_multiple_of(LOG_SECTOR)
+        || length > log_len
+        || !tail.is_multiple_of(LOG_SECTOR)
+        || tail >= log_len
+        || read_guid(header, 32) != log_guid
+    {
+        return None;
+    }
+
+    // The entry may wrap around the end of the log
+    let entry: Vec<u8> = (0..length / LOG_SECTOR)
+        .flat_map(|index| sector(index).iter().copied())
+        .collect();
+    if !checksum_matches(&entry, 4) {
+        return None;
+    }
+
+    let descriptor_sectors =
+        ((LOG_HEADER_SIZE + descriptors * LOG_DESCRIPTOR_SIZE) as u64).div_ceil(LOG_SECTOR);
+    let mut data_sector = descriptor_sectors;
+    let mut writes = Vec::with_capacity(descriptors);
+    for index in 0..descriptors {
+        let at = LOG_HEADER_SIZE + index * LOG_DESCRIPTOR_SIZE;
+        let descriptor = entry.get(at..at + LOG_DESCRIPTOR_SIZE)?;
+        if le_u64(descriptor, 24) != sequence {
+            return None;
+        }
+        let file_offset = le_u64(descriptor, 16);
+        match &descriptor[0..4] {
+            signature if signature == LOG_ZERO_SIGNATURE => writes.push(LogWrite::Zero {
+                offset: file_offset,
+                length: le_u64(descriptor, 8),
+            }),
+            signature if signature == LOG_DESC_SIGNATURE => {
+                let at = (data_sector * LOG_SECTOR) as usize;
+                let data = entry.get(at..at + LOG_SECTOR as usize)?;
+                let data_sequence =
+                    u64::from(le_u32(data, 4)) << 32 | u64::from(le_u32(data, 4092));
+                if &data[0..4] != LOG_DATA_SIGNATURE || data_sequence != sequence {
+                    return None;
+                }
+                // The first 8 and last 4 bytes of the sector are kept in
+                // the descriptor, the data sector holds the rest
+                let mut page = Vec::with_capacity(LOG_SECTOR as usize);
+                page.extend_from_slice(&descriptor[8..16]);
+                page.extend_from_slice(&data[8..4092]);
+                page.extend_from_slice(&descriptor[4..8]);
+                writes.push(LogWrite::Data {
+                    offset: file_offset,
+                    data: page,
+                });
+                data_sector += 1;
+            }
+            _ => return None,
+        }
+    }
+    if data_sector * LOG_SECTOR > length {
+        return None;
+    }
+
+    Some(LogEntry {
+        offset,
+        length,
+        tail,
+        sequence,
+        last_file_offset: le_u64(header, 56),
+        writes,
+    })
+}
+
+/// Key/value pairs of a VHDX parent locator
+fn parse_parent_locator(item: &[u8]) -> Option<Vec<(String, String)>> {
+    if item.len() < 20 || read_guid(item, 0) != VHDX_PARENT_LOCATOR {
+        return None;
+    }
+    let count = usize::from(le_u16(item, 18));
+    let utf16 = |offset: u32, length: u16| {
+        let bytes = item.get(offset as usize..offset as usize + usize::from(length))?;
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+            .collect();
+        String::from_utf16(&units).ok()
+    };
+
+    (0..count)
+        .map(|index| {
+            let entry = item.get(20 + index * 12..20 + (index + 1) * 12)?;
+            let key = utf16(le_u32(entry, 0), le_u16(entry, 8))?;
+            let value = utf16(le_u32(entry, 4), le_u16(entry, 10))?;
+            Some((key, value))
+        })
+        .collect()
+}
+
+/// CRC-32C lookup table (Castagnoli polynomial, reflected)
+pub(super) const CRC32C_TABLE: [u32; 256] = {
+    let mut table = [0u32; 256];
+    let mut index = 0;
+    while index < 256 {
+        let mut crc = index as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 {
+                (crc >> 1) ^ 0x82f6_3b78
+            } else {
+                crc >> 1
+            };
+            bit += 1;
+        }
+        table[index] = crc;
+        index += 1;
+    }
+    table
+};
+
+fn crc32c(data: &[u8]) -> u32 {
+    !data.iter().fold(!0u32, |crc, &byte| {
+        CRC32C_TABLE[((crc ^ u32::from(byte)) & 0xff) as usize] ^ (crc >> 8)
+    })
+}
+
+/// VHDX structures are checksummed with their checksum field zeroed
+fn checksum_matches(buf: &[u8], field: usize) -> bool {
+    let stored = le_u32(buf, field);
+    let mut copy = buf.to_vec();
+    copy[field..field + 4].fill(0);
+    crc32c(&copy) == stored
+}
+
+/// GUIDs are stored with their first three fields little-endian
+fn read_guid(buf: &[u8], offset: usize) -> Uuid {
+    Uuid::from_bytes_le(buf[offset..offset + 16].try_into().unwrap())
+}
+
+fn le_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_le_bytes(buf[offset..offset + 2].try_into().unwrap())
+}
+
+fn le_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn le_u64(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const BLOCK: u64 = MIB;
+    const LOG_AT: u64 = MIB;
+    const METADATA_AT: u64 = 2 * MIB;
+    const BAT_AT: u64 = 3 * MIB;
+
+    fn put(image: &mut Vec<u8>, offset: u64, bytes: &[u8]) {
+        let offset = offset as usize;
+        if image.len() < offset + bytes.len() {
+            image.resize(offset + bytes.len(), 0);
+        }
+        image[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn seal(buf: &mut [u8], field: usize) {
+        buf[field..field + 4].fill(0);
+        let crc = crc32c(buf);
+        buf[field..field + 4].copy_from_slice(&crc.to_le_bytes());
+    }
+
+    fn write_headers(image: &mut Vec<u8>, data_write_guid: Uuid, log_guid: Uuid) {
+        for (sequence, offset) in HEADER_OFFSETS.into_iter().enumerate() {
+            let mut header = vec![0u8; HEADER_SIZE];
+            header[0..4].copy_from_slice(HEADER_SIGNATURE);
+            header[8..16].copy_from_slice(&(sequence as u64 + 1).to_le_bytes());
+            header[32..48].copy_from_slice(&data_write_guid.to_bytes_le());
+            header[48..64].copy_from_slice(&log_guid.to_bytes_le());
+            header[66..68].copy_from_slice(&1u16.to_le_bytes());
+            header[68..72].copy_from_slice(&(MIB as u32).to_le_bytes());
+            header[72..80].copy_from_slice(&LOG_AT.to_le_bytes());
+            seal(&mut header, 4);
+            put(image, offset, &header);
+        }
+    }
+
+    fn utf16(text: &str) -> Vec<u8> {
+        text.encode_utf16().flat_map(u16::to_le_bytes).collect()
+    }
+
+    /// An 8 MiB VHDX with 1 MiB blocks and nothing allocated
+    fn new_image(data_write_guid: Uuid, parent: Option<(&str, Uuid)>) -> Vec<u8> {
+        let mut image = vec![0u8; 4 * MIB as usize];
+        put(&mut image, 0, FILE_SIGNATURE);
+        write_headers(&mut image, data_write_guid, Uuid::nil());
+
+        let mut regions = vec![0u8; REGION_TABLE_SIZE];
+        regions[0..4].copy_from_slice(REGION_SIGNATURE);
+        regions[8..12].copy_from_slice(&2u32.to_le_bytes());
+        for (index, (guid, offset)) in [(BAT_REGION, BAT_AT), (METADATA_REGION, METADATA_AT)]
+            .into_iter()
+            .enumerate()
+        {
+            let entry = &mut regions[16 + index * 32..16 + (index + 1) * 32];
+            entry[0..16].copy_from_slice(&guid.to_bytes_le());
+            entry[16..24].copy_from_slice(&offset.to_le_bytes());
+            entry[24..28].copy_from_slice(&(MIB as u32).to_le_bytes());
+            entry[28..32].copy_from_slice(&1u32.to_le_bytes());
+        }
+        seal(&mut regions, 4);
+        for offset in REGION_TABLE_OFFSETS {
+            put(&mut image, offset, &regions);
+        }
+
+        let flags = if parent.is_some() { HAS_PARENT } else { 0 };
+        let mut file_parameters = (BLOCK as u32).to_le_bytes().to_vec();
+        file_parameters.extend_from_slice(&flags.to_le_bytes());
+        let mut items = vec![
+            (FILE_PARAMETERS, file_parameters),
+            (VIRTUAL_DISK_SIZE, (8 * MIB).to_le_bytes().to_vec()),
+            (VIRTUAL_DISK_ID, Uuid::new_v4().to_bytes_le().to_vec()),
+            (LOGICAL_SECTOR_SIZE, 512u32.to_le_bytes().to_vec()),
+            (PHYSICAL_SECTOR_SIZE, 4096u32.to_le_bytes().to_vec()),
+        ];
+        if let Some((path, linkage)) = parent {
+            let pairs = [
+                ("parent_linkage", format!("{{{}}}", linkage)),
+                ("relative_path", path.to_string()),
+            ];
+            let mut locator = VHDX_PARENT_LOCATOR.to_bytes_le().to_vec();
+            locator.extend_from_slice(&[0, 0]);
+            locator.extend_from_slice(&(pairs.len() as u16).to_le_bytes());
+            let mut strings = Vec::new();
+            let base = 20 + 12 * pairs.len();
+            for (key, value) in &pairs {
+                let (key, value) = (utf16(key), utf16(value));
+                let key_offset = (base + strings.len()) as u32;
+                strings.extend_from_slice(&key);
+                let value_offset = (base + strings.len()) as u32;
+                strings.extend_from_slice(&value);
+                locator.extend_from_slice(&key_offset.to_le_bytes());
+                locator.extend_from_slice(&value_offset.to_le_bytes());
+                locator.extend_from_slice(&(key.len() as u16).to_le_bytes());
+                locator.extend_from_slice(&(value.len() as u16).to_le_bytes());
+            }
+            locator.extend_from_slice(&strings);
+            items.push((PARENT_LOCATOR, locator));
+        }
+
+        let mut table = vec![0u8; METADATA_TABLE_SIZE];
+        table[0..8].copy_from_slice(METADATA_SIGNATURE);
+        table[10..12].copy_from_slice(&(items.len() as u16).to_le_bytes());
+        let mut offset = METADATA_TABLE_SIZE as u64;
+        for (index, (id, data)) in items.iter().enumerate() {
+            let entry = &mut table[32 + index * 32..32 + (index + 1) * 32];
+            entry[0..16].copy_from_slice(&id.to_bytes_le());
+            entry[16..20].copy_from_slice(&(offset as u32).to_le_bytes());
+            entry[20..24].copy_from_slice(&(data.len() as u32).to_le_bytes());
+            entry[24..28].copy_from_slice(&METADATA_IS_REQUIRED.to_le_bytes());
+            put(&mut image, METADATA_AT + offset, data);
+            offset += 4096;
+        }
+        put(&mut image, METADATA_AT, &table);
+        image
+    }
+
+    fn set_bat(image: &mut Vec<u8>, index: u64, state: u64, offset: u64) {
+        put(image, BAT_AT + index * 8, &(offset | state).to_le_bytes());
+    }
+
+    /// Append a block holding `data` and return its file offset
+    fn add_block(image: &mut Vec<u8>, data: &[u8]) -> u64 {
+        let offset = image.len() as u64;
+        image.resize((offset + BLOCK) as usize, 0);
+        put(image, offset, data);
+        offset
+    }
+
+    /// A log entry applying `page` at `file_offset`
+    fn log_entry(log_guid: Uuid, sequence: u64, file_offset: u64, page: &[u8]) -> Vec<u8> {
+        let mut entry = vec![0u8; 2 * LOG_SECTOR as usize];
+        entry[0..4].copy_from_slice(LOG_ENTRY_SIGNATURE);
+        entry[8..12].copy_from_slice(&(2 * LOG_SECTOR as u32).to_le_bytes());
+        entry[16..24].copy_from_slice(&sequence.to_le_bytes());
+        entry[24..28].copy_from_slice(&1u32.to_le_bytes());
+        entry[32..48].copy_from_slice(&log_guid.to_bytes_le());
+        entry[56..64].copy_from_slice(&(16 * MIB).to_le_bytes());
+
+        let descriptor = &mut entry[64..96];
+        descriptor[0..4].copy_from_slice(LOG_DESC_SIGNATURE);
+        descriptor[4..8].copy_from_slice(&page[4092..4096]);
+        descriptor[8..16].copy_from_slice(&page[0..8]);
+        descriptor[16..24].copy_from_slice(&file_offset.to_le_bytes());
+        descriptor[24..32].copy_from_slice(&sequence.to_le_bytes());
+
+        let data = &mut entry[4096..8192];
+        data[0..4].copy_from_slice(LOG_DATA_SIGNATURE);
+        data[4..8].copy_from_slice(&((sequence >> 32) as u32).to_le_bytes());
+        data[8..4092].copy_from_slice(&page[8..4092]);
+        data[4092..4096].copy_from_slice(&(sequence as u32).to_le_bytes());
+        seal(&mut entry, 4);
+        entry
+    }
+
+    #[test]
+    fn test_crc32c() {
+        assert_eq!(crc32c(b"123456789"), 0xe306_9283);
+        assert_eq!(crc32c(b""), 0);
+    }
+
+    #[test]
+    fn test_dynamic() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dc01.vhdx");
+        let mut image = new_image(Uuid::new_v4(), None);
+        let first = add_block(&mut image, &[0xaa; 4096]);
+        set_bat(&mut image, 2, PAYLOAD_FULLY_PRESENT, first);
+        set_bat(&mut image, 3, 2, 0);
+        std::fs::write(&path, &image).unwrap();
+        assert!(is_vhdx(&path));
+
+        let vhdx = VhdxImage::open(&path).unwrap();
+        assert_eq!(vhdx.virtual_size, 8 * MIB);
+        assert_eq!(vhdx.block_size, BLOCK as u32);
+        assert_eq!(
+            (vhdx.logical_sector_size, vhdx.physical_sector_size),
+            (512, 4096)
+        );
+        assert!(!vhdx.log_replayed);
+        assert!(!vhdx.is_differencing());
+
+        // Spanning an unallocated block and an allocated one
+        let mut buf = vec![0xffu8; 8192];
+        vhdx.read_at(2 * BLOCK - 4096, &mut buf).unwrap();
+        assert!(buf[..4096].iter().all(|&byte| byte == 0));
+        assert!(buf[4096..].iter().all(|&byte| byte == 0xaa));
+        assert!(vhdx.read_at(8 * MIB - 1, &mut buf).is_err());
+        assert_eq!(
+            vhdx.allocation(),
+            vec![Extent {
+                offset: 2 * BLOCK,
+                length: BLOCK
+            }]
+        );
+
+        let raw = dir.path().join("dc01.raw");
+        vhdx.write_raw(&raw).unwrap();
+        let raw = std::fs::read(&raw).unwrap();
+        assert_eq!(raw.len() as u64, 8 * MIB);
+        assert_eq!(raw[2 * BLOCK as usize], 0xaa);
+        assert_eq!(raw[2 * BLOCK as usize + 4096], 0);
+    }
+
+    #[test]
+    fn test_log_replay() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("dirty.vhdx");
+        let mut image = new_image(Uuid::new_v4(), None);
+        let block = add_block(&mut image, b"written before the crash");
+
+        // The BAT update pointing at the block only made it to the log
+        let log_guid = Uuid::new_v4();
+        let mut page = image[BAT_AT as usize..(BAT_AT + LOG_SECTOR) as usize].to_vec();
+        page[0..8].copy_from_slice(&(block | PAYLOAD_FULLY_PRESENT).to_le_bytes());
+        put(&mut image, LOG_AT, &log_entry(log_guid, 7, BAT_AT, &page));
+        std::fs::write(&path, &image).unwrap();
+
+        // Without a log GUID in the header the log is stale
+        let vhdx = VhdxImage::open(&path).unwrap();
+        assert!(!vhdx.log_replayed);
+        let mut buf = [0u8; 24];
+        vhdx.read_at(0, &mut buf).unwrap();
+        assert_eq!(buf, [0u8; 24]);
+
+        write_headers(&mut image, Uuid::new_v4(), log_guid);
+        std::fs::write(&path, &image).unwrap();
+        let vhdx = VhdxImage::open(&path).unwrap();
+        assert!(vhdx.log_replayed);
+        vhdx.read_at(0, &mut buf).unwrap();
+        assert_eq!(&buf, b"written before the crash");
+        // The file on disk is left alone
+        assert_eq!(std::fs::read(&path).unwrap(), image);
+
+        // An entry that fails its checksum isn't replayed
+        image[(LOG_AT + 4200) as usize] ^= 1;
+        std::fs::write(&path, &image).unwrap();
+        assert!(!VhdxImage::open(&path).unwrap().log_replayed);
+    }
+
+    #[test]
+    fn test_differencing() {
+        let dir = tempfile::tempdir().unwrap();
+        let parent_guid = Uuid::new_v4();
+        let mut parent = new_image(parent_guid, None);
+        for block in 0..2 {
+            let offset = add_block(&mut parent, &vec![b'p'; BLOCK as usize]);
+            set_bat(&mut parent, block, PAYLOAD_FULLY_PRESENT, offset);
+        }
+        std::fs::write(dir.path().join("base.vhdx"), &parent).unwrap();
+
+        let path = dir.path().join("child.vhdx");
+        let mut child = new_image(Uuid::new_v4(), Some((".\\base.vhdx", parent_guid)));
+        // Block 1 has its second sector only
+        let offset = add_block(&mut child, &vec![b'c'; BLOCK as usize]);
+        set_bat(&mut child, 1, PAYLOAD_PARTIALLY_PRESENT, offset);
+        let mut bitmap = vec![0u8; MIB as usize];
+        let sector = BLOCK / 512 + 1;
+        bitmap[(sector / 8) as usize] |= 1 << (sector % 8);
+        let bitmap = add_block(&mut child, &bitmap);
+        set_bat(&mut child, 4096, SECTOR_BITMAP_PRESENT, bitmap);
+        std::fs::write(&path, &child).unwrap();
+
+        let vhdx = VhdxImage::open(&path).unwrap();
+        assert!(vhdx.is_differencing());
+        assert_eq!(vhdx.parent.as_deref(), Some(".\\base.vhdx"));
+
+        let mut buf = vec![0u8; 3 * 512];
+        vhdx.read_at(BLOCK, &mut buf).unwrap();
+        assert!(buf[..512].iter().all(|&byte| byte == b'p'));
+        assert!(buf[512..1024].iter().all(|&byte| byte == b'c'));
+        assert!(buf[1024..].iter().all(|&byte| byte == b'p'));
+        vhdx.read_at(0, &mut buf[..4]).unwrap();
+        assert_eq!(&buf[..4], b"pppp");
+        vhdx.read_at(4 * BLOCK, &mut buf[..4]).unwrap();
+        assert_eq!(&buf[..4], &[0; 4]);
+        assert_eq!(
+            vhdx.allocation(),
+            vec![Extent {
+                offset: 0,
+                length: 2 * BLOCK
+            }]
+        );
+
+        // The parent changed since the child was created
+        write_headers(&mut parent, Uuid::new_v4(), Uuid::nil());
+        std::fs::write(dir.path().join("base.vhdx"), &parent).unwrap();
+        let err = VhdxImage::open(&path).unwrap_err();
+        assert!(err.to_string().contains("modified"), "{}", err);
+
+        std::fs::remove_file(dir.path().join("base.vhdx")).unwrap();
+        let err = VhdxImage::open(&path).unwrap_err();
+        assert!(matches!(err, Error::NotFound(_)), "{}", err);
+    }
+}
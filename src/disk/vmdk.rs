@@ -0,0 +1,616 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! VMDK header and descriptor parsing
+//!
+//! VMDK is a family of layouts sharing one text descriptor, either in a
+//! file of its own or embedded in a sparse extent. QEMU reads all the
+//! variants we receive, but two of them cannot be written in place:
+//!
+//! - streamOptimized, the layout OVAs carry: deflate-compressed grains
+//!   with the grain directory in a footer at the end of the file
+//! - seSparse, the extents vSphere uses for snapshots
+//!
+//! [`VmdkImage::open`] reads the descriptor to tell the variants apart and
+//! checks the pieces QEMU will need, so a truncated download or a snapshot
+//! copied without its parent fails with a clear error before attaching.
+//!
+//! [`VmdkImage::reader`] reads the guest data without QEMU, from flat,
+//! sparse and streamOptimized extents, split or not, and their parents.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use guestkit::disk::VmdkImage;
+//!
+//! let vmdk = VmdkImage::open("/srv/import/appliance-disk1.vmdk")?;
+//! println!("{} ({} bytes)", vmdk.subformat, vmdk.capacity);
+//! for layer in vmdk.backing_chain()? {
+//!     println!("  backed by {}", layer.path.display());
+//! }
+//! # Ok::<(), guestkit::Error>(())
+//! ```
+
+use crate::core::{Error, Result};
+use std::fmt;
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+
+const SECTOR: u64 = 512;
+
+/// Hosted sparse extent header magic ("KDMV")
+const SPARSE_MAGIC: &[u8; 4] = b"KDMV";
+/// ESX sparse extent header magic, used by vmfsSparse snapshots
+const COWD_MAGIC: &[u8; 4] = b"COWD";
+/// seSparse constant header magic and the only version QEMU reads
+const SESPARSE_MAGIC: u64 = 0xcafe_babe;
+const SESPARSE_VERSION: u64 = 0x2_0000_0001;
+
+/// First line of every descriptor
+pub(crate) const DESCRIPTOR_SIGNATURE: &[u8] = b"# Disk DescriptorFile";
+/// Descriptors are a few hundred bytes; refuse anything that isn't one
+const MAX_DESCRIPTOR_SIZE: u64 = 64 * 1024;
+
+/// Sparse header flag: grains are compressed
+const FLAG_COMPRESSED: u32 = 1 << 16;
+const COMPRESSION_DEFLATE: u16 = 1;
+/// streamOptimized writers only know the grain directory once done
+const GD_AT_END: u64 = u64::MAX;
+/// Footer marker, footer header and end-of-stream marker close the stream
+const FOOTER_OFFSET_FROM_END: u64 = 2 * SECTOR;
+
+/// Longest snapshot chain followed before assuming a loop
+const MAX_CHAIN_LENGTH: usize = 64;
+
+mod reader;
+
+pub use reader::VmdkReader;
+
+/// VMDK layout, from the descriptor's `createType`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum VmdkSubformat {
+    MonolithicSparse,
+    MonolithicFlat,
+    TwoGbMaxExtentSparse,
+    TwoGbMaxExtentFlat,
+    StreamOptimized,
+    SeSparse,
+    VmfsSparse,
+    Vmfs,
+    Other(String),
+}
+
+impl VmdkSubformat {
+    pub fn from_create_type(create_type: &str) -> Self {
+        match create_type {
+            "monolithicSparse" => VmdkSubformat::MonolithicSparse,
+            "monolithicFlat" => VmdkSubformat::MonolithicFlat,
+            "twoGbMaxExtentSparse" => VmdkSubformat::TwoGbMaxExtentSparse,
+            "twoGbMaxExtentFlat" => VmdkSubformat::TwoGbMaxExtentFlat,
+            "streamOptimized" => VmdkSubformat::StreamOptimized,
+            "seSparse" => VmdkSubformat::SeSparse,
+            "vmfsSparse" => VmdkSubformat::VmfsSparse,
+            "vmfs" => VmdkSubformat::Vmfs,
+            other => VmdkSubformat::Other(other.to_string()),
+        }
+    }
+
+    /// Whether QEMU can write to this layout in place
+    pub fn is_writable(&self) -> bool {
+        !matches!(
+            self,
+            VmdkSubformat::StreamOptimized | VmdkSubformat::SeSparse
+        )
+    }
+}
+
+impl fmt::Display for VmdkSubformat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            VmdkSubformat::MonolithicSparse => "monolithicSparse",
+            VmdkSubformat::MonolithicFlat => "monolithicFlat",
+            VmdkSubformat::TwoGbMaxExtentSparse => "twoGbMaxExtentSparse",
+            VmdkSubformat::TwoGbMaxExtentFlat => "twoGbMaxExtentFlat",
+            VmdkSubformat::StreamOptimized => "streamOptimized",
+            VmdkSubformat::SeSparse => "seSparse",
+            VmdkSubformat::VmfsSparse => "vmfsSparse",
+            VmdkSubformat::Vmfs => "vmfs",
+            VmdkSubformat::Other(name) => name,
+        };
+        f.write_str(name)
+    }
+}
+
+/// One extent line of a descriptor, e.g. `RW 41943040 SESPARSE "disk-sesparse.vmdk"`
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VmdkExtent {
+    /// RW, RDONLY or NOACCESS
+    pub access: String,
+    /// Size in bytes
+    pub size: u64,
+    /// SPARSE, FLAT, ZERO, VMFS, VMFSSPARSE, SESPARSE, ...
+    pub kind: String,
+    /// Extent file, relative to the descriptor (none for ZERO extents)
+    pub file: Option<String>,
+    /// Byte offset of the data within a FLAT extent file
+    pub offset: u64,
+}
+
+/// A VMDK descriptor and the header of the file holding it
+#[derive(Debug, Clone)]
+pub struct VmdkImage {
+    /// File the descriptor was read from
+    pub path: PathBuf,
+    pub subformat: VmdkSubformat,
+    /// Virtual disk size in bytes
+    pub capacity: u64,
+    pub extents: Vec<VmdkExtent>,
+    /// Parent disk of a snapshot, as named by `parentFileNameHint`
+    pub parent: Option<String>,
+    /// `ddb.adapterType`, e.g. lsilogic or pvscsi
+    pub adapter_type: Option<String>,
+}
+
+impl VmdkImage {
+    /// Read the descriptor of a VMDK and check its extents are present
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(Error::Io)?;
+        let len = file.metadata().map_err(Error::Io)?.len();
+
+        let mut magic = [0u8; 8];
+        let read = file.read_at(&mut magic, 0).map_err(Error::Io)?;
+        let magic = &magic[..read];
+
+        let mut image = if magic.starts_with(SPARSE_MAGIC) {
+            Self::open_sparse(path, &file, len)?
+        } else if magic.starts_with(COWD_MAGIC) {
+            Self::open_cowd(path, &file)?
+        } else if read == 8 && u64::from_le_bytes(magic.try_into().unwrap()) == SESPARSE_MAGIC {
+            return Err(Error::InvalidFormat(format!(
+                "{} is a seSparse extent; open the descriptor that references it",
+                path.display()
+            )));
+        } else if DESCRIPTOR_SIGNATURE.starts_with(magic) && !magic.is_empty() {
+            if len > MAX_DESCRIPTOR_SIZE {
+                return Err(Error::InvalidFormat(format!(
+                    "{} is too large for a VMDK descriptor",
+                    path.display()
+                )));
+            }
+            let text = std::fs::read_to_string(path).map_err(Error::Io)?;
+            let image = Self::from_descriptor(path, &text, 0)?;
+            image.check_extents()?;
+            image
+        } else {
+            return Err(Error::InvalidFormat(format!(
+                "{} is not a VMDK",
+                path.display()
+            )));
+        };
+
+        if image.capacity == 0 {
+            image.capacity = image.extents.iter().map(|extent| extent.size).sum();
+        }
+        Ok(image)
+    }
+
+    /// Whether the disk can be attached read-write
+    ///
+    /// Snapshots write to their own layer only, so a writable snapshot of a
+    /// streamOptimized parent is fine.
+    pub fn is_writable(&self) -> bool {
+        self.subformat.is_writable() && !self.extents.iter().any(|extent| extent.kind == "SESPARSE")
+    }
+
+    /// Directory extent and parent file names are relative to
+    fn base_dir(&self) -> &Path {
+        self.path.parent().unwrap_or_else(|| Path::new("."))
+    }
+
+    /// Path of an extent file
+    pub fn extent_path(&self, extent: &VmdkExtent) -> Option<PathBuf> {
+        extent.file.as_ref().map(|file| self.base_dir().join(file))
+    }
+
+    /// Path of the parent disk, resolved the way QEMU does
+    pub fn parent_path(&self) -> Option<PathBuf> {
+        self.parent
+            .as_ref()
+            .map(|parent| self.base_dir().join(parent))
+    }
+
+    /// Open every parent of a snapshot, nearest first
+    pub fn backing_chain(&self) -> Result<Vec<VmdkImage>> {
+        let mut chain: Vec<VmdkImage> = Vec::new();
+        let mut next = self.parent_path();
+        while let Some(path) = next {
+            if chain.len() == MAX_CHAIN_LENGTH {
+                return Err(Error::InvalidFormat(format!(
+                    "{}: snapshot chain longer than {} disks",
+                    self.path.display(),
+                    MAX_CHAIN_LENGTH
+                )));
+            }
+            if !path.exists() {
+                let child = chain.last().map_or(&self.path, |layer| &layer.path);
+                return Err(Error::NotFound(format!(
+                    "Parent disk {} of {} is missing; copy it alongside or fix parentFileNameHint",
+                    path.display(),
+                    child.display()
+                )));
+            }
+            let layer = VmdkImage::open(&path)?;
+            next = layer.parent_path();
+            chain.push(layer);
+        }
+        Ok(chain)
+    }
+
+    /// Hosted sparse extent: monolithicSparse, streamOptimized or one
+    /// extent of twoGbMaxExtentSparse, with the descriptor embedded
+    fn open_sparse(path: &Path, file: &File, len: u64) -> Result<Self> {
+        let mut header = [0u8; 79];
+        file.read_exact_at(&mut header, 0).map_err(|_| {
+            Error::InvalidFormat(format!("{}: truncated VMDK header", path.display()))
+        })?;
+
+        let flags = le_u32(&header, 8);
+        let capacity = le_u64(&header, 12)
+            .checked_mul(SECTOR)
+            .ok_or_else(|| Error::InvalidFormat(format!("{}: invalid capacity", path.display())))?;
+        let descriptor_offset = le_u64(&header, 28);
+        let descriptor_size = le_u64(&header, 36);
+        let gd_offset = le_u64(&header, 56);
+        let compression = u16::from_le_bytes([header[77], header[78]]);
+
+        if flags & FLAG_COMPRESSED != 0 && compression != COMPRESSION_DEFLATE {
+            return Err(Error::Unsupported(format!(
+                "{}: unknown VMDK grain compression {}",
+                path.display(),
+                compression
+            )));
+        }
+
+        // The footer repeats the header with the real grain directory offset;
+        // a stream cut short by an interrupted download has none
+        if gd_offset == GD_AT_END {
+            let mut footer = [0u8; 4];
+            let present = len >= FOOTER_OFFSET_FROM_END + 3 * SECTOR
+                && file
+                    .read_exact_at(&mut footer, len - FOOTER_OFFSET_FROM_END)
+                    .is_ok()
+                && &footer == SPARSE_MAGIC;
+            if !present {
+                return Err(Error::InvalidFormat(format!(
+                    "{} is truncated: the streamOptimized footer is missing",
+                    path.display()
+                )));
+            }
+        }
+
+        // Extents of a split sparse disk carry no descriptor of their own
+        if descriptor_offset == 0 || descriptor_size == 0 {
+            return Ok(Self {
+                path: path.to_path_buf(),
+                subformat: VmdkSubformat::Other("sparse extent".to_string()),
+                capacity,
+                extents: Vec::new(),
+                parent: None,
+                adapter_type: None,
+            });
+        }
+
+        let size = descriptor_size * SECTOR;
+        if size > MAX_DESCRIPTOR_SIZE || descriptor_offset.saturating_mul(SECTOR) >= len {
+            return Err(Error::InvalidFormat(format!(
+                "{}: invalid embedded descriptor",
+                path.display()
+            )));
+        }
+        let mut descriptor = vec![0u8; size as usize];
+        let read = file
+            .read_at(&mut descriptor, descriptor_offset * SECTOR)
+            .map_err(Error::Io)?;
+        descriptor.truncate(read);
+        if let Some(end) = descriptor.iter().position(|&byte| byte == 0) {
+            descriptor.truncate(end);
+        }
+
+        // QEMU reads the grains from this file whatever the extent line
+        // says, and OVA payloads are often renamed, so extents aren't checked
+        Self::from_descriptor(path, &String::from_utf8_lossy(&descriptor), capacity)
+    }
+
+    /// ESX sparse extent of a vmfsSparse snapshot, opened directly
+    fn open_cowd(path: &Path, file: &File) -> Result<Self> {
+        let mut header = [0u8; 16];
+        file.read_exact_at(&mut header, 0).map_err(|_| {
+            Error::InvalidFormat(format!("{}: truncated VMDK header", path.display()))
+        })?;
+        Ok(Self {
+            path: path.to_path_buf(),
+            subformat: VmdkSubformat::VmfsSparse,
+            capacity: u64::from(le_u32(&header, 12)) * SECTOR,
+            extents: Vec::new(),
+            parent: None,
+            adapter_type: None,
+        })
+    }
+
+    fn from_descriptor(path: &Path, text: &str, capacity: u64) -> Result<Self> {
+        let mut create_type = None;
+        let mut parent = None;
+        let mut adapter_type = None;
+        let mut extents = Vec::new();
+
+        for line in text.lines().map(str::trim) {
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            if line.starts_with("RW ")
+                || line.starts_with("RDONLY ")
+                || line.starts_with("NOACCESS ")
+            {
+                let extent = parse_extent(line).ok_or_else(|| {
+                    Error::InvalidFormat(format!(
+                        "{}: invalid extent line '{}'",
+                        path.display(),
+                        line
+                    ))
+                })?;
+                extents.push(extent);
+                continue;
+            }
+            let Some((key, value)) = line.split_once('=') else {
+                continue;
+            };
+            let value = value.trim().trim_matches('"');
+            match key.trim() {
+                "createType" => create_type = Some(value.to_string()),
+                "parentFileNameHint" if !value.is_empty() => parent = Some(value.to_string()),
+                "ddb.adapterType" => adapter_type = Some(value.to_string()),
+                _ => {}
+            }
+        }
+
+        let create_type = create_type.ok_or_else(|| {
+            Error::InvalidFormat(format!(
+                "{}: VMDK descriptor has no createType",
+                path.display()
+            ))
+        })?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            subformat: VmdkSubformat::from_create_type(&create_type),
+            capacity,
+            extents,
+            parent,
+            adapter_type,
+        })
+    }
+
+    /// Every extent file must exist; seSparse ones must be a version QEMU reads
+    fn check_extents(&self) -> Result<()> {
+        for extent in &self.extents {
+            let Some(extent_path) = self.extent_path(extent) else {
+                continue;
+            };
+            let file = File::open(&extent_path).map_err(|_| {
+                Error::NotFound(format!(
+                    "Extent {} of {} is missing",
+                    extent_path.display(),
+                    self.path.display()
+                ))
+            })?;
+
+            if extent.kind == "SESPARSE" {
+                let mut header = [0u8; 16];
+                file.read_exact_at(&mut header, 0).map_err(|_| {
+                    Error::InvalidFormat(format!(
+                        "{}: truncated seSparse header",
+                        extent_path.display()
+                    ))
+                })?;
+                if le_u64(&header, 0) != SESPARSE_MAGIC {
+                    return Err(Error::InvalidFormat(format!(
+                        "{} is not a seSparse extent",
+                        extent_path.display()
+                    )));
+                }
+                let version = le_u64(&header, 8);
+                if version != SESPARSE_VERSION {
+                    return Err(Error::Unsupported(format!(
+                        "{}: seSparse version {:#x}",
+                        extent_path.display(),
+                        version
+                    )));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Parse `ACCESS SECTORS TYPE ["FILE" [OFFSET]]`
+fn parse_extent(line: &str) -> Option<VmdkExtent> {
+    let (head, quoted) = match line.split_once('"') {
+        Some((head, rest)) => (head, Some(rest)),
+        None => (line, None),
+    };
+    let mut fields = head.split_whitespace();
+    let access = fields.next()?.to_string();
+    let sectors: u64 = fields.next()?.parse().ok()?;
+    let kind = fields.next()?.to_string();
+
+    let (file, offset) = match quoted {
+        Some(rest) => {
+            let (file, tail) = rest.split_once('"')?;
+            let offset = match tail.trim() {
+                "" => 0,
+                sectors => sectors.parse::<u64>().ok()?.checked_mul(SECTOR)?,
+            };
+            (Some(file.to_string()), offset)
+        }
+        None => (None, 0),
+    };
+
+    Some(VmdkExtent {
+        access,
+        size: sectors.checked_mul(SECTOR)?,
+        kind,
+        file,
+        offset,
+    })
+}
+
+fn le_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn le_u64(buf: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Hosted sparse header with an embedded descriptor at sector 1
+    fn sparse_image(path: &Path, descriptor: &str, stream: bool, complete: bool) {
+        let mut buf = vec![0u8; 8 * SECTOR as usize];
+        buf[0..4].copy_from_slice(SPARSE_MAGIC);
+        buf[4..8].copy_from_slice(&3u32.to_le_bytes());
+        let flags = if stream { FLAG_COMPRESSED | 1 << 17 } else { 0 };
+        buf[8..12].copy_from_slice(&flags.to_le_bytes());
+        buf[12..20].copy_from_slice(&(1u64 << 21).to_le_bytes());
+        buf[20..28].copy_from_slice(&128u64.to_le_bytes());
+        buf[28..36].copy_from_slice(&1u64.to_le_bytes());
+        buf[36..44].copy_from_slice(&2u64.to_le_bytes());
+        let gd_offset = if stream { GD_AT_END } else { 3 };
+        buf[56..64].copy_from_slice(&gd_offset.to_le_bytes());
+        if stream {
+            buf[77..79].copy_from_slice(&COMPRESSION_DEFLATE.to_le_bytes());
+        }
+        buf[512..512 + descriptor.len()].copy_from_slice(descriptor.as_bytes());
+        if stream && complete {
+            // Footer marker, footer header, end-of-stream marker
+            let footer = buf.len() - FOOTER_OFFSET_FROM_END as usize;
+            let header = buf[0..512].to_vec();
+            buf[footer..footer + 512].copy_from_slice(&header);
+        }
+        std::fs::write(path, buf).unwrap();
+    }
+
+    fn descriptor(create_type: &str, extent: &str, parent: Option<&str>) -> String {
+        let mut text = format!(
+            "# Disk DescriptorFile\nversion=1\nCID=fffffffe\nparentCID=ffffffff\n\
+             createType=\"{}\"\n",
+            create_type
+        );
+        if let Some(parent) = parent {
+            text.push_str(&format!("parentFileNameHint=\"{}\"\n", parent));
+        }
+        text.push_str(&format!(
+            "\n# Extent description\n{}\n\n# The Disk Data Base\n#DDB\n\n\
+             ddb.adapterType = \"lsilogic\"\n",
+            extent
+        ));
+        text
+    }
+
+    #[test]
+    fn test_stream_optimized() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("appliance-disk1.vmdk");
+        let text = descriptor(
+            "streamOptimized",
+            "RW 2097152 SPARSE \"appliance-1.0-disk1.vmdk\"",
+            None,
+        );
+        sparse_image(&path, &text, true, true);
+
+        let vmdk = VmdkImage::open(&path).unwrap();
+        assert_eq!(vmdk.subformat, VmdkSubformat::StreamOptimized);
+        assert_eq!(vmdk.capacity, 1 << 30);
+        assert_eq!(vmdk.adapter_type.as_deref(), Some("lsilogic"));
+        assert!(!vmdk.is_writable());
+        assert!(vmdk.backing_chain().unwrap().is_empty());
+
+        sparse_image(&path, &text, true, false);
+        let err = VmdkImage::open(&path).unwrap_err();
+        assert!(err.to_string().contains("truncated"), "{}", err);
+    }
+
+    #[test]
+    fn test_sesparse_snapshot() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("db.vmdk");
+        sparse_image(
+            &base,
+            &descriptor("monolithicSparse", "RW 2097152 SPARSE \"db.vmdk\"", None),
+            false,
+            true,
+        );
+        let snapshot = dir.path().join("db-000001.vmdk");
+        std::fs::write(
+            &snapshot,
+            descriptor(
+                "seSparse",
+                "RW 2097152 SESPARSE \"db-000001-sesparse.vmdk\"",
+                Some("db.vmdk"),
+            ),
+        )
+        .unwrap();
+
+        // The extent is not there yet
+        let err = VmdkImage::open(&snapshot).unwrap_err();
+        assert!(matches!(err, Error::NotFound(_)), "{}", err);
+
+        let mut header = SESPARSE_MAGIC.to_le_bytes().to_vec();
+        header.extend_from_slice(&SESPARSE_VERSION.to_le_bytes());
+        let extent = dir.path().join("db-000001-sesparse.vmdk");
+        std::fs::write(&extent, &header).unwrap();
+
+        let vmdk = VmdkImage::open(&snapshot).unwrap();
+        assert_eq!(vmdk.subformat, VmdkSubformat::SeSparse);
+        assert_eq!(vmdk.capacity, 1 << 30);
+        assert_eq!(vmdk.extents[0].kind, "SESPARSE");
+        assert!(!vmdk.is_writable());
+        let chain = vmdk.backing_chain().unwrap();
+        assert_eq!(chain.len(), 1);
+        assert_eq!(chain[0].subformat, VmdkSubformat::MonolithicSparse);
+        assert!(chain[0].is_writable());
+
+        // Opening the extent itself points at the descriptor
+        let err = VmdkImage::open(&extent).unwrap_err();
+        assert!(err.to_string().contains("descriptor"), "{}", err);
+
+        std::fs::remove_file(&base).unwrap();
+        let err = vmdk.backing_chain().unwrap_err();
+        assert!(matches!(err, Error::NotFound(_)), "{}", err);
+    }
+
+    #[test]
+    fn test_parse_extent() {
+        assert_eq!(
+            parse_extent("RW 4192256 FLAT \"disk-flat.vmdk\" 2048"),
+            Some(VmdkExtent {
+                access: "RW".to_string(),
+                size: 4192256 * SECTOR,
+                kind: "FLAT".to_string(),
+                file: Some("disk-flat.vmdk".to_string()),
+                offset: 2048 * SECTOR,
+            })
+        );
+        assert_eq!(parse_extent("RW 2048 ZERO").unwrap().file, None);
+        assert_eq!(
+            parse_extent("RDONLY 8 SPARSE \"my disk.vmdk\"")
+                .unwrap()
+                .file
+                .as_deref(),
+            Some("my disk.vmdk")
+        );
+        assert_eq!(parse_extent("RW lots SPARSE \"x.vmdk\""), None);
+    }
+}
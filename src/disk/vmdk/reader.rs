@@ -0,0 +1,755 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Guest data of a VMDK, read from its extents
+//!
+//! The extents of a descriptor make up the disk one after the other: flat
+//! ones hold the data as is, sparse ones map it through a grain directory
+//! and grain tables, and streamOptimized ones deflate every grain. Grains
+//! a snapshot doesn't have are read from its parent.
+
+use super::{
+    le_u32, le_u64, VmdkImage, VmdkSubformat, COMPRESSION_DEFLATE, FLAG_COMPRESSED,
+    FOOTER_OFFSET_FROM_END, GD_AT_END, MAX_CHAIN_LENGTH, SECTOR, SPARSE_MAGIC,
+};
+use crate::core::{Error, Result};
+use crate::disk::allocation::{Extent, LayerMap};
+use flate2::read::ZlibDecoder;
+use std::fs::File;
+use std::io::Read;
+use std::os::unix::fs::FileExt;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Sparse header flag: every grain starts with a marker holding its size
+const FLAG_MARKERS: u32 = 1 << 17;
+/// Grain table entry of a grain that reads as zeros
+const GTE_ZERO: u64 = 1;
+/// Grain marker: guest sector and compressed size
+const MARKER_SIZE: u64 = 12;
+
+/// QEMU's limits
+const MAX_GRAIN_SECTORS: u64 = 1 << 16;
+const MAX_GTES_PER_GT: u32 = 512;
+
+/// Reads the guest disk of a VMDK, see [`VmdkImage::reader`]
+pub struct VmdkReader {
+    pub path: PathBuf,
+    /// Virtual disk size in bytes
+    pub capacity: u64,
+    extents: Vec<OpenExtent>,
+    parent: Option<Box<VmdkReader>>,
+}
+
+/// An extent and the guest bytes it covers
+struct OpenExtent {
+    start: u64,
+    size: u64,
+    data: ExtentData,
+}
+
+enum ExtentData {
+    Flat { file: File, offset: u64 },
+    Zero,
+    Sparse(SparseExtent),
+}
+
+/// Hosted sparse extent, compressed or not
+struct SparseExtent {
+    path: PathBuf,
+    file: File,
+    len: u64,
+    /// Bytes per grain
+    grain_size: u64,
+    gtes_per_gt: u64,
+    /// Sector of each grain table, 0 if none was allocated
+    directory: Vec<u32>,
+    compressed: bool,
+    markers: bool,
+    /// Last grain inflated; reads of a compressed extent are mostly sequential
+    inflated: Mutex<Option<(u64, Vec<u8>)>>,
+}
+
+impl VmdkImage {
+    /// Open the extents and parents of the disk to read its guest data
+    ///
+    /// seSparse and vmfsSparse snapshots, and raw device mappings, are
+    /// not supported.
+    pub fn reader(&self) -> Result<VmdkReader> {
+        VmdkReader::open(self, 0)
+    }
+}
+
+impl VmdkReader {
+    fn open(image: &VmdkImage, depth: usize) -> Result<Self> {
+        if matches!(
+            image.subformat,
+            VmdkSubformat::SeSparse | VmdkSubformat::VmfsSparse
+        ) {
+            return Err(Error::Unsupported(format!(
+                "{}: reading {} VMDKs",
+                image.path.display(),
+                image.subformat
+            )));
+        }
+
+        // A sparse file with an embedded descriptor (or a split extent
+        // opened on its own) is a single extent whatever the descriptor says
+        let mut magic = [0u8; 4];
+        File::open(&image.path)
+            .and_then(|file| file.read_exact_at(&mut magic, 0))
+            .map_err(Error::Io)?;
+        let extents = if &magic == SPARSE_MAGIC {
+            vec![OpenExtent {
+                start: 0,
+                size: image.capacity,
+                data: ExtentData::Sparse(SparseExtent::open(&image.path)?),
+            }]
+        } else {
+            let mut start = 0;
+            let mut extents = Vec::with_capacity(image.extents.len());
+            for extent in &image.extents {
+                let extent_path = || {
+                    image.extent_path(extent).ok_or_else(|| {
+                        Error::InvalidFormat(format!(
+                            "{}: {} extent without a file",
+                            image.path.display(),
+                            extent.kind
+                        ))
+                    })
+                };
+                let data = match extent.kind.as_str() {
+                    "FLAT" | "VMFS" => ExtentData::Flat {
+                        file: File::open(extent_path()?).map_err(Error::Io)?,
+                        offset: extent.offset,
+                    },
+                    "ZERO" => ExtentData::Zero,
+                    "SPARSE" => ExtentData::Sparse(SparseExtent::open(&extent_path()?)?),
+                    kind => {
+                        return Err(Error::Unsupported(format!(
+                            "{}: reading {} extents",
+                            image.path.display(),
+                            kind
+                        )))
+                    }
+                };
+                extents.push(OpenExtent {
+                    start,
+                    size: extent.size,
+                    data,
+                });
+                start += extent.size;
+            }
+            extents
+        };
+
+        let parent = match image.parent_path() {
+            Some(_) if depth + 1 >= MAX_CHAIN_LENGTH => {
+                return Err(Error::InvalidFormat(format!(
+                    "{}: snapshot chain longer than {} disks",
+                    image.path.display(),
+                    MAX_CHAIN_LENGTH
+                )))
+            }
+            Some(path) if !path.exists() => {
+                return Err(Error::NotFound(format!(
+                    "Parent disk {} of {} is missing; copy it alongside or fix parentFileNameHint",
+                    path.display(),
+                    image.path.display()
+                )))
+            }
+            Some(path) => Some(Box::new(Self::open(&VmdkImage::open(&path)?, depth + 1)?)),
+            None => None,
+        };
+
+        Ok(Self {
+            path: image.path.clone(),
+            capacity: image.capacity,
+            extents,
+            parent,
+        })
+    }
+
+    /// Read guest data; the whole range must lie within the virtual disk
+    pub fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        let in_range = offset
+            .checked_add(buf.len() as u64)
+            .is_some_and(|end| end <= self.capacity);
+        if !in_range {
+            return Err(Error::InputValidation(format!(
+                "Read of {} bytes at {} is beyond the {} byte disk",
+                buf.len(),
+                offset,
+                self.capacity
+            )));
+        }
+
+        let mut done = 0;
+        while done < buf.len() {
+            let position = offset + done as u64;
+            let index = self
+                .extents
+                .partition_point(|extent| extent.start + extent.size <= position);
+            let Some(extent) = self.extents.get(index) else {
+                // The extents are shorter than the disk
+                buf[done..].fill(0);
+                break;
+            };
+            let within = position - extent.start;
+            let length = (buf.len() - done).min((extent.size - within) as usize);
+            let part = &mut buf[done..done + length];
+
+            match &extent.data {
+                ExtentData::Flat { file, offset } => file
+                    .read_exact_at(part, offset + within)
+                    .map_err(|e| short_read(&self.path, e))?,
+                ExtentData::Zero => part.fill(0),
+                ExtentData::Sparse(sparse) => {
+                    sparse.read(within, part, self.parent.as_deref(), extent.start)?
+                }
+            }
+            done += length;
+        }
+        Ok(())
+    }
+
+    /// Guest ranges this disk or its parents have data for
+    pub fn allocation(&self) -> Result<Vec<Extent>> {
+        let mut map = LayerMap::default();
+        for extent in &self.extents {
+            match &extent.data {
+                ExtentData::Flat { .. } => map.data(extent.start, extent.size),
+                ExtentData::Zero => {}
+                ExtentData::Sparse(sparse) => {
+                    sparse.allocation(extent.start, extent.size, &mut map)?
+                }
+            }
+        }
+        map.resolve(
+            self.parent
+                .as_deref()
+                .map(|parent| move || parent.allocation()),
+        )
+    }
+}
+
+impl SparseExtent {
+    fn open(path: &Path) -> Result<Self> {
+        let file = File::open(path).map_err(Error::Io)?;
+        let len = file.metadata().map_err(Error::Io)?.len();
+        let truncated = || Error::InvalidFormat(format!("{}: truncated VMDK", path.display()));
+
+        let mut header = [0u8; SECTOR as usize];
+        file.read_exact_at(&mut header, 0)
+            .map_err(|_| truncated())?;
+        if &header[0..4] != SPARSE_MAGIC {
+            return Err(Error::InvalidFormat(format!(
+                "{} is not a sparse VMDK extent",
+                path.display()
+            )));
+        }
+
+        // streamOptimized: the footer has the grain directory offset
+        if le_u64(&header, 56) == GD_AT_END {
+            if len < FOOTER_OFFSET_FROM_END {
+                return Err(truncated());
+            }
+            file.read_exact_at(&mut header, len - FOOTER_OFFSET_FROM_END)
+                .map_err(|_| truncated())?;
+            if &header[0..4] != SPARSE_MAGIC {
+                return Err(Error::InvalidFormat(format!(
+                    "{} is truncated: the streamOptimized footer is missing",
+                    path.display()
+                )));
+            }
+        }
+
+        let flags = le_u32(&header, 8);
+        let capacity = le_u64(&header, 12);
+        let grain_sectors = le_u64(&header, 20);
+        let gtes_per_gt = le_u32(&header, 44);
+        let gd_offset = le_u64(&header, 56);
+        let compressed = flags & FLAG_COMPRESSED != 0;
+
+        if grain_sectors == 0 || grain_sectors > MAX_GRAIN_SECTORS {
+            return Err(Error::InvalidFormat(format!(
+                "{}: invalid grain size of {} sectors",
+                path.display(),
+                grain_sectors
+            )));
+        }
+        if gtes_per_gt == 0 || gtes_per_gt > MAX_GTES_PER_GT {
+            return Err(Error::InvalidFormat(format!(
+                "{}: invalid grain table size {}",
+                path.display(),
+                gtes_per_gt
+            )));
+        }
+        if compressed && u16::from_le_bytes([header[77], header[78]]) != COMPRESSION_DEFLATE {
+            return Err(Error::Unsupported(format!(
+                "{}: unknown VMDK grain compression",
+                path.display()
+            )));
+        }
+
+        let entries = capacity.div_ceil(grain_sectors * u64::from(gtes_per_gt));
+        let directory_offset = gd_offset.checked_mul(SECTOR).ok_or_else(truncated)?;
+        if directory_offset + entries * 4 > len {
+            return Err(truncated());
+        }
+        let mut directory = vec![0u8; (entries * 4) as usize];
+        file.read_exact_at(&mut directory, directory_offset)
+            .map_err(Error::Io)?;
+
+        Ok(Self {
+            path: path.to_path_buf(),
+            file,
+            len,
+            grain_size: grain_sectors * SECTOR,
+            gtes_per_gt: u64::from(gtes_per_gt),
+            directory: directory
+                .chunks_exact(4)
+                .map(|entry| le_u32(entry, 0))
+                .collect(),
+            compressed,
+            markers: flags & FLAG_MARKERS != 0,
+            inflated: Mutex::new(None),
+        })
+    }
+
+    /// Read `buf` from `within` bytes into the extent, which starts at
+    /// guest offset `start`
+    fn read(
+        &self,
+        within: u64,
+        buf: &mut [u8],
+        parent: Option<&VmdkReader>,
+        start: u64,
+    ) -> Result<()> {
+        let mut done = 0;
+        while done < buf.len() {
+            let position = within + done as u64;
+            let in_grain = position % self.grain_size;
+            let length = (buf.len() - done).min((self.grain_size - in_grain) as usize);
+            let part = &mut buf[done..done + length];
+
+            match self.grain_sector(position / self.grain_size)? {
+                0 => match parent {
+                    Some(parent) => parent.read_at(start + position, part)?,
+                    None => part.fill(0),
+                },
+                GTE_ZERO => part.fill(0),
+                sector if self.compressed => {
+                    let mut inflated = self.inflated.lock().unwrap_or_else(|e| e.into_inner());
+                    if inflated
+                        .as_ref()
+                        .is_none_or(|(cached, _)| *cached != sector)
+                    {
+                        *inflated = Some((sector, self.inflate(sector)?));
+                    }
+                    let grain = &inflated.as_ref().unwrap().1;
+                    part.copy_from_slice(&grain[in_grain as usize..in_grain as usize + length]);
+                }
+                sector => self
+                    .file
+                    .read_exact_at(part, sector * SECTOR + in_grain)
+                    .map_err(|e| short_read(&self.path, e))?,
+            }
+            done += length;
+        }
+        Ok(())
+    }
+
+    /// Add the grains of the extent, `size` bytes from guest offset
+    /// `start`, to `map`; a grain table at a time
+    fn allocation(&self, start: u64, size: u64, map: &mut LayerMap) -> Result<()> {
+        let table_span = self.grain_size * self.gtes_per_gt;
+        let mut position = 0;
+        for &table in &self.directory {
+            if position >= size {
+                break;
+            }
+            if table == 0 {
+                map.unallocated(start + position, table_span.min(size - position));
+                position += table_span;
+                continue;
+            }
+            let entries = self
+                .gtes_per_gt
+                .min((size - position).div_ceil(self.grain_size));
+            let mut buf = vec![0u8; (entries * 4) as usize];
+            self.file
+                .read_exact_at(&mut buf, u64::from(table) * SECTOR)
+                .map_err(|e| short_read(&self.path, e))?;
+            for entry in buf.chunks_exact(4) {
+                let length = self.grain_size.min(size - position);
+                match u64::from(le_u32(entry, 0)) {
+                    0 => map.unallocated(start + position, length),
+                    GTE_ZERO => {}
+                    _ => map.data(start + position, length),
+                }
+                position += self.grain_size;
+            }
+        }
+        // Past the end of the directory counts as unallocated too
+        if position < size {
+            map.unallocated(start + position, size - position);
+        }
+        Ok(())
+    }
+
+    /// Sector of a grain, 0 if unallocated or [`GTE_ZERO`]
+    fn grain_sector(&self, grain: u64) -> Result<u64> {
+        // Past the end of the directory counts as unallocated too
+        let table = self
+            .directory
+            .get((grain / self.gtes_per_gt) as usize)
+            .copied()
+            .unwrap_or(0);
+        if table == 0 {
+            return Ok(0);
+        }
+        let mut entry = [0u8; 4];
+        self.file
+            .read_exact_at(
+                &mut entry,
+                u64::from(table) * SECTOR + (grain % self.gtes_per_gt) * 4,
+            )
+            .map_err(|e| short_read(&self.path, e))?;
+        Ok(u64::from(u32::from_le_bytes(entry)))
+    }
+
+    /// Decompress the grain stored at `sector`
+    fn inflate(&self, sector: u64) -> Result<Vec<u8>> {
+        let offset = sector * SECTOR;
+        // Without markers the stream length isn't stored; a grain never
+        // compresses to much more than its size
+        let (offset, size) = if self.markers {
+            let mut marker = [0u8; MARKER_SIZE as usize];
+            self.file
+                .read_exact_at(&mut marker, offset)
+                .map_err(|e| short_read(&self.path, e))?;
+            (offset + MARKER_SIZE, u64::from(le_u32(&marker, 8)))
+        } else {
+            (
+                offset,
+                (2 * self.grain_size).min(self.len.saturating_sub(offset)),
+            )
+        };
+        if size > 2 * self.grain_size || offset + size > self.len {
+            return Err(Error::InvalidFormat(format!(
+                "{}: invalid compressed grain at sector {}",
+                self.path.display(),
+                sector
+            )));
+        }
+
+        let mut compressed = vec![0u8; size as usize];
+        self.file
+            .read_exact_at(&mut compressed, offset)
+            .map_err(|e| short_read(&self.path, e))?;
+        let mut grain = Vec::with_capacity(self.grain_size as usize);
+        ZlibDecoder::new(compressed.as_slice())
+            .take(self.grain_size + 1)
+            .read_to_end(&mut grain)
+            .map_err(|e| {
+                Error::InvalidFormat(format!(
+                    "{}: corrupt grain at sector {}: {}",
+                    self.path.display(),
+                    sector,
+                    e
+                ))
+            })?;
+        if grain.len() as u64 > self.grain_size {
+            return Err(Error::InvalidFormat(format!(
+                "{}: grain at sector {} inflates past the grain size",
+                self.path.display(),
+                sector
+            )));
+        }
+        // The last grain of the disk may be short
+        grain.resize(self.grain_size as usize, 0);
+        Ok(grain)
+    }
+}
+
+fn short_read(path: &Path, err: std::io::Error) -> Error {
+    if err.kind() == std::io::ErrorKind::UnexpectedEof {
+        Error::InvalidFormat(format!("{} is truncated", path.display()))
+    } else {
+        Error::Io(err)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::ZlibEncoder;
+    use flate2::Compression;
+    use std::io::Write;
+
+    /// 4 KiB grains; one grain table covers 2 MiB
+    const GRAIN_SECTORS: u64 = 8;
+    const GRAIN: usize = (GRAIN_SECTORS * SECTOR) as usize;
+
+    fn put(image: &mut Vec<u8>, offset: u64, bytes: &[u8]) {
+        let offset = offset as usize;
+        if image.len() < offset + bytes.len() {
+            image.resize(offset + bytes.len(), 0);
+        }
+        image[offset..offset + bytes.len()].copy_from_slice(bytes);
+    }
+
+    fn pad_to_sector(image: &mut Vec<u8>) {
+        image.resize(image.len().div_ceil(SECTOR as usize) * SECTOR as usize, 0);
+    }
+
+    fn header(capacity: u64, flags: u32, descriptor: bool, gd_offset: u64) -> Vec<u8> {
+        let mut header = vec![0u8; SECTOR as usize];
+        header[0..4].copy_from_slice(SPARSE_MAGIC);
+        header[4..8].copy_from_slice(&3u32.to_le_bytes());
+        header[8..12].copy_from_slice(&flags.to_le_bytes());
+        header[12..20].copy_from_slice(&(capacity / SECTOR).to_le_bytes());
+        header[20..28].copy_from_slice(&GRAIN_SECTORS.to_le_bytes());
+        if descriptor {
+            header[28..36].copy_from_slice(&1u64.to_le_bytes());
+            header[36..44].copy_from_slice(&2u64.to_le_bytes());
+        }
+        header[44..48].copy_from_slice(&512u32.to_le_bytes());
+        header[56..64].copy_from_slice(&gd_offset.to_le_bytes());
+        if flags & FLAG_COMPRESSED != 0 {
+            header[77..79].copy_from_slice(&COMPRESSION_DEFLATE.to_le_bytes());
+        }
+        header
+    }
+
+    /// Hosted sparse extent of `capacity` bytes holding `grains`
+    /// (grain index, content; an empty content is a zeroed grain)
+    fn sparse_extent(capacity: u64, grains: &[(u64, &[u8])], descriptor: Option<&str>) -> Vec<u8> {
+        let tables = capacity.div_ceil(GRAIN_SECTORS * SECTOR * 512);
+        let gd_sector = 4;
+        let mut image = header(capacity, 0, descriptor.is_some(), gd_sector);
+        if let Some(descriptor) = descriptor {
+            put(&mut image, SECTOR, descriptor.as_bytes());
+        }
+        let first_table = gd_sector + 1;
+        for table in 0..tables {
+            let sector = (first_table + table * 4) as u32;
+            put(
+                &mut image,
+                gd_sector * SECTOR + table * 4,
+                &sector.to_le_bytes(),
+            );
+        }
+        let mut next = (first_table + tables * 4) * SECTOR;
+        for (grain, data) in grains {
+            let entry = first_table * SECTOR + grain * 4;
+            if data.is_empty() {
+                put(&mut image, entry, &(GTE_ZERO as u32).to_le_bytes());
+                continue;
+            }
+            put(&mut image, entry, &((next / SECTOR) as u32).to_le_bytes());
+            let mut grain = data.to_vec();
+            grain.resize(GRAIN, 0);
+            put(&mut image, next, &grain);
+            next += GRAIN as u64;
+        }
+        pad_to_sector(&mut image);
+        image
+    }
+
+    /// streamOptimized extent: compressed grains behind markers, then the
+    /// grain table, directory and footer
+    fn stream_optimized(capacity: u64, grains: &[(u64, &[u8])], descriptor: &str) -> Vec<u8> {
+        let flags = FLAG_COMPRESSED | FLAG_MARKERS;
+        let mut image = header(capacity, flags, true, GD_AT_END);
+        put(&mut image, SECTOR, descriptor.as_bytes());
+        image.resize(4 * SECTOR as usize, 0);
+
+        let mut table = vec![0u8; 512 * 4];
+        for (grain, data) in grains {
+            let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+            encoder.write_all(data).unwrap();
+            let compressed = encoder.finish().unwrap();
+            let sector = image.len() as u64 / SECTOR;
+            put(&mut table, grain * 4, &(sector as u32).to_le_bytes());
+            image.extend_from_slice(&(grain * GRAIN_SECTORS).to_le_bytes());
+            image.extend_from_slice(&(compressed.len() as u32).to_le_bytes());
+            image.extend_from_slice(&compressed);
+            pad_to_sector(&mut image);
+        }
+        let table_sector = image.len() as u64 / SECTOR;
+        image.extend_from_slice(&table);
+        let gd_sector = image.len() as u64 / SECTOR;
+        image.extend_from_slice(&(table_sector as u32).to_le_bytes());
+        pad_to_sector(&mut image);
+
+        // Footer marker, footer, end-of-stream marker
+        image.resize(image.len() + SECTOR as usize, 0);
+        image.extend_from_slice(&header(capacity, flags, true, gd_sector));
+        image.resize(image.len() + SECTOR as usize, 0);
+        image
+    }
+
+    fn descriptor(create_type: &str, extents: &[&str], parent: Option<&str>) -> String {
+        let mut text = format!(
+            "# Disk DescriptorFile\nversion=1\ncreateType=\"{}\"\n",
+            create_type
+        );
+        if let Some(parent) = parent {
+            text.push_str(&format!("parentFileNameHint=\"{}\"\n", parent));
+        }
+        text.push_str("\n# Extent description\n");
+        for extent in extents {
+            text.push_str(extent);
+            text.push('\n');
+        }
+        text
+    }
+
+    fn read(reader: &VmdkReader, offset: u64, length: usize) -> Vec<u8> {
+        let mut buf = vec![0xffu8; length];
+        reader.read_at(offset, &mut buf).unwrap();
+        buf
+    }
+
+    #[test]
+    fn test_monolithic_sparse() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("disk.vmdk");
+        let text = descriptor("monolithicSparse", &["RW 8192 SPARSE \"disk.vmdk\""], None);
+        let image = sparse_extent(
+            4 << 20,
+            &[(1, b"grain one"), (2, b""), (600, b"second table")],
+            Some(&text),
+        );
+        std::fs::write(&path, image).unwrap();
+
+        let reader = VmdkImage::open(&path).unwrap().reader().unwrap();
+        assert_eq!(reader.capacity, 4 << 20);
+        assert_eq!(&read(&reader, GRAIN as u64, 9), b"grain one");
+        assert_eq!(read(&reader, 0, GRAIN), vec![0; GRAIN]);
+        assert_eq!(read(&reader, 2 * GRAIN as u64, 16), vec![0; 16]);
+        assert_eq!(&read(&reader, 600 * GRAIN as u64, 12), b"second table");
+        // Across an unallocated grain and an allocated one
+        let both = read(&reader, GRAIN as u64 - 4, 8);
+        assert_eq!(&both, b"\0\0\0\0grai");
+        assert!(reader.read_at(4 << 20, &mut [0u8; 1]).is_err());
+
+        // The zeroed grain isn't allocated
+        let extent = |grain: u64| Extent {
+            offset: grain * GRAIN as u64,
+            length: GRAIN as u64,
+        };
+        assert_eq!(reader.allocation().unwrap(), vec![extent(1), extent(600)]);
+    }
+
+    #[test]
+    fn test_stream_optimized() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("appliance-disk1.vmdk");
+        let text = descriptor(
+            "streamOptimized",
+            &["RW 2048 SPARSE \"renamed.vmdk\""],
+            None,
+        );
+        let first = vec![b'a'; GRAIN];
+        let second: Vec<u8> = (0..GRAIN).map(|i| (i % 251) as u8).collect();
+        let image = stream_optimized(1 << 20, &[(0, &first), (1, &second)], &text);
+        std::fs::write(&path, &image).unwrap();
+
+        let reader = VmdkImage::open(&path).unwrap().reader().unwrap();
+        let data = read(&reader, GRAIN as u64 - 2, 4);
+        assert_eq!(data, [b'a', b'a', 0, 1]);
+        assert_eq!(read(&reader, GRAIN as u64, GRAIN), second);
+        assert_eq!(read(&reader, 2 * GRAIN as u64, 4), [0; 4]);
+
+        // A damaged deflate stream is reported, not read as zeros
+        let mut damaged = image.clone();
+        damaged[4 * SECTOR as usize + 20] ^= 0xff;
+        std::fs::write(&path, &damaged).unwrap();
+        let reader = VmdkImage::open(&path).unwrap().reader().unwrap();
+        assert!(reader.read_at(0, &mut [0u8; 16]).is_err());
+    }
+
+    #[test]
+    fn test_split_extents() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("split.vmdk");
+        let mut flat = vec![0u8; 1024];
+        flat.extend_from_slice(&[b'f'; 4096]);
+        std::fs::write(dir.path().join("split-f001.vmdk"), &flat).unwrap();
+        std::fs::write(
+            dir.path().join("split-s002.vmdk"),
+            sparse_extent(8192, &[(1, b"sparse")], None),
+        )
+        .unwrap();
+        let text = descriptor(
+            "twoGbMaxExtentSparse",
+            &[
+                "RW 8 FLAT \"split-f001.vmdk\" 2",
+                "RW 8 ZERO",
+                "RW 16 SPARSE \"split-s002.vmdk\"",
+            ],
+            None,
+        );
+        std::fs::write(&path, text).unwrap();
+
+        let reader = VmdkImage::open(&path).unwrap().reader().unwrap();
+        assert_eq!(reader.capacity, 32 * SECTOR);
+        let data = read(&reader, 0, reader.capacity as usize);
+        assert!(data[..4096].iter().all(|&byte| byte == b'f'));
+        assert!(data[4096..12288].iter().all(|&byte| byte == 0));
+        assert_eq!(&data[12288..12294], b"sparse");
+        assert!(data[12294..].iter().all(|&byte| byte == 0));
+        assert_eq!(
+            reader.allocation().unwrap(),
+            vec![
+                Extent {
+                    offset: 0,
+                    length: 4096
+                },
+                Extent {
+                    offset: 12288,
+                    length: 4096
+                }
+            ]
+        );
+
+        std::fs::remove_file(dir.path().join("split-s002.vmdk")).unwrap();
+        assert!(VmdkImage::open(&path).is_err());
+    }
+
+    #[test]
+    fn test_snapshot_reads_parent() {
+        let dir = tempfile::tempdir().unwrap();
+        let base = dir.path().join("db.vmdk");
+        let text = descriptor("monolithicSparse", &["RW 2048 SPARSE \"db.vmdk\""], None);
+        std::fs::write(
+            &base,
+            sparse_extent(1 << 20, &[(0, b"base zero"), (1, b"base one")], Some(&text)),
+        )
+        .unwrap();
+
+        let snapshot = dir.path().join("db-000001.vmdk");
+        let text = descriptor(
+            "monolithicSparse",
+            &["RW 2048 SPARSE \"db-000001.vmdk\""],
+            Some("db.vmdk"),
+        );
+        std::fs::write(
+            &snapshot,
+            sparse_extent(1 << 20, &[(1, b"snap one")], Some(&text)),
+        )
+        .unwrap();
+
+        let reader = VmdkImage::open(&snapshot).unwrap().reader().unwrap();
+        assert_eq!(&read(&reader, 0, 9), b"base zero");
+        assert_eq!(&read(&reader, GRAIN as u64, 8), b"snap one");
+        assert_eq!(
+            reader.allocation().unwrap(),
+            vec![Extent {
+                offset: 0,
+                length: 2 * GRAIN as u64
+            }]
+        );
+    }
+}
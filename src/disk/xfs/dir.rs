@@ -0,0 +1,294 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Directories and path lookup
+//!
+//! Small directories are short form, their entries in the inode. Larger
+//! ones keep their entries in data blocks, followed in the file by hash
+//! indexes this reader doesn't need: a single block directory has its
+//! leaf in the same block, leaf and node directories in blocks past
+//! `LEAF_OFFSET`.
+
+use super::inode::{Inode, FORMAT_LOCAL};
+use super::layout::checksum_matches;
+use super::Xfs;
+use crate::core::{Error, Result};
+use std::collections::BTreeMap;
+
+/// Data blocks of single block directories, and of larger ones
+const BLOCK_MAGIC: &[u8; 4] = b"XD2B";
+const DATA_MAGIC: &[u8; 4] = b"XD2D";
+const BLOCK3_MAGIC: &[u8; 4] = b"XDB3";
+const DATA3_MAGIC: &[u8; 4] = b"XDD3";
+const HEADER_SIZE: usize = 16;
+const HEADER3_SIZE: usize = 64;
+const HEADER3_CRC_OFFSET: usize = 4;
+const HEADER3_OWNER_OFFSET: usize = 40;
+
+/// Where leaf and free index blocks start in a directory
+const LEAF_OFFSET: u64 = 32 << 30;
+
+/// Unused space in a data block starts with this tag and its length
+const FREE_TAG: u16 = 0xffff;
+
+/// Remote symbolic link blocks of v5 filesystems
+const SYMLINK_MAGIC: &[u8; 4] = b"XSLM";
+const SYMLINK_HEADER_SIZE: usize = 56;
+const MAX_PATH_LEN: u64 = 1024;
+
+const FT_DIR: u8 = 2;
+
+const MAX_SYMLINKS: usize = 40;
+
+fn be_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn be_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn be_u64(buf: &[u8], offset: usize) -> u64 {
+    u64::from_be_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
+
+/// A directory entry, with the file type if the filesystem records it
+pub(super) struct DirEntry {
+    pub name: Vec<u8>,
+    pub ino: u64,
+    pub file_type: u8,
+}
+
+fn corrupt(dir: &Inode) -> Error {
+    Error::InvalidFormat(format!("directory inode {} is corrupt", dir.ino))
+}
+
+impl Xfs {
+    /// Entries of directory `dir`, "." and ".." included
+    pub(super) fn entries(&self, dir: &Inode) -> Result<Vec<DirEntry>> {
+        if dir.format() == FORMAT_LOCAL {
+            self.short_form_entries(dir)
+        } else {
+            self.block_entries(dir)
+        }
+    }
+
+    /// Entries of a short form directory, where "." and ".." are implied
+    fn short_form_entries(&self, dir: &Inode) -> Result<Vec<DirEntry>> {
+        let data = dir.local_data(dir.size())?;
+        let ftype = self.sb.ftype();
+        let dir_type = if ftype { FT_DIR } else { 0 };
+        if data.len() < 2 {
+            return Err(corrupt(dir));
+        }
+        let count = usize::from(data[0]);
+        let ino_size = if data[1] > 0 { 8 } else { 4 };
+        let read_ino = |offset: usize| -> Result<u64> {
+            let bytes = data
+                .get(offset..offset + ino_size)
+                .ok_or_else(|| corrupt(dir))?;
+            Ok(if ino_size == 8 {
+                be_u64(bytes, 0) & ((1 << 56) - 1)
+            } else {
+                u64::from(be_u32(bytes, 0))
+            })
+        };
+
+        let mut entries = vec![
+            DirEntry {
+                name: b".".to_vec(),
+                ino: dir.ino,
+                file_type: dir_type,
+            },
+            DirEntry {
+                name: b"..".to_vec(),
+                ino: read_ino(2)?,
+                file_type: dir_type,
+            },
+        ];
+        let mut pos = 2 + ino_size;
+        for _ in 0..count {
+            // Name length, a 16-bit offset kept for readdir cookies, the
+            // name, the file type and the inode number
+            let name_len = usize::from(*data.get(pos).ok_or_else(|| corrupt(dir))?);
+            let name_start = pos + 3;
+            let name = data
+                .get(name_start..name_start + name_len)
+                .ok_or_else(|| corrupt(dir))?;
+            pos = name_start + name_len;
+            let file_type = if ftype {
+                pos += 1;
+                *data.get(pos - 1).ok_or_else(|| corrupt(dir))?
+            } else {
+                0
+            };
+            entries.push(DirEntry {
+                name: name.to_vec(),
+                ino: read_ino(pos)?,
+                file_type,
+            });
+            pos += ino_size;
+        }
+        Ok(entries)
+    }
+
+    /// Entries of the data blocks of a block, leaf or node directory
+    fn block_entries(&self, dir: &Inode) -> Result<Vec<DirEntry>> {
+        let block_size = self.sb.block_size;
+        let dir_block_size = self.sb.dir_block_size() as usize;
+        let per_dir_block = 1u64 << self.sb.dir_block_log;
+
+        // Directory blocks may span several extents
+        let mut blocks: BTreeMap<u64, Vec<u8>> = BTreeMap::new();
+        for extent in self.extents(dir)?.iter().filter(|e| !e.unwritten) {
+            for i in 0..extent.len {
+                let logical = extent.logical + i;
+                if logical * block_size >= LEAF_OFFSET {
+                    break;
+                }
+                let block = self.read_fs_block(extent.fsb + i)?;
+                let buf = blocks
+                    .entry(logical / per_dir_block)
+                    .or_insert_with(|| vec![0u8; dir_block_size]);
+                let at = ((logical % per_dir_block) * block_size) as usize;
+                buf[at..at + block.len()].copy_from_slice(&block);
+            }
+        }
+
+        let ftype = self.sb.ftype();
+        let mut entries = Vec::new();
+        for block in blocks.values() {
+            let (header, single) = match &block[0..4] {
+                magic if magic == BLOCK_MAGIC && !self.sb.v5() => (HEADER_SIZE, true),
+                magic if magic == DATA_MAGIC && !self.sb.v5() => (HEADER_SIZE, false),
+                magic if magic == BLOCK3_MAGIC && self.sb.v5() => (HEADER3_SIZE, true),
+                magic if magic == DATA3_MAGIC && self.sb.v5() => (HEADER3_SIZE, false),
+                _ => return Err(corrupt(dir)),
+            };
+            if self.sb.v5()
+                && (!checksum_matches(block, HEADER3_CRC_OFFSET)
+                    || be_u64(block, HEADER3_OWNER_OFFSET) != dir.ino)
+            {
+                return Err(Error::InvalidFormat(format!(
+                    "directory inode {}: block checksum mismatch",
+                    dir.ino
+                )));
+            }
+            // A single block ends with its leaf entries and their count
+            let end = if single {
+                let leaves = be_u32(block, dir_block_size - 8) as usize;
+                dir_block_size
+                    .checked_sub(leaves.saturating_mul(8).saturating_add(8))
+                    .filter(|&end| end >= header)
+                    .ok_or_else(|| corrupt(dir))?
+            } else {
+                dir_block_size
+            };
+
+            let mut pos = header;
+            while pos + 4 <= end {
+                if be_u16(block, pos) == FREE_TAG {
+                    let len = usize::from(be_u16(block, pos + 2));
+                    if len == 0 || len % 8 != 0 {
+                        return Err(corrupt(dir));
+                    }
+                    pos += len;
+                    continue;
+                }
+                // Inode number, name length, name, file type and a tag,
+                // padded to 8 bytes
+                let name_len = usize::from(*block.get(pos + 8).ok_or_else(|| corrupt(dir))?);
+                let extra = usize::from(ftype);
+                let len = (8 + 1 + name_len + extra + 2 + 7) & !7;
+                if name_len == 0 || pos + len > end {
+                    return Err(corrupt(dir));
+                }
+                entries.push(DirEntry {
+                    name: block[pos + 9..pos + 9 + name_len].to_vec(),
+                    ino: be_u64(block, pos),
+                    file_type: if ftype { block[pos + 9 + name_len] } else { 0 },
+                });
+                pos += len;
+            }
+        }
+        Ok(entries)
+    }
+
+    pub(super) fn lookup(&self, dir: &Inode, name: &[u8]) -> Result<Option<DirEntry>> {
+        Ok(self
+            .entries(dir)?
+            .into_iter()
+            .find(|entry| entry.name == name))
+    }
+
+    /// Inode at `path`, following a final symbolic link if `follow`
+    pub(super) fn resolve(&self, path: &str, follow: bool) -> Result<Inode> {
+        let mut pending: Vec<Vec<u8>> = components(path).rev().collect();
+        let mut current = self.read_inode(self.sb.root_ino)?;
+        let mut symlinks = 0;
+        while let Some(name) = pending.pop() {
+            if !current.is_dir() {
+                return Err(Error::NotFound(format!("{}: not a directory", path)));
+            }
+            let entry = self
+                .lookup(&current, &name)?
+                .ok_or_else(|| Error::NotFound(path.to_string()))?;
+            let inode = self.read_inode(entry.ino)?;
+            if inode.is_symlink() && (follow || !pending.is_empty()) {
+                symlinks += 1;
+                if symlinks > MAX_SYMLINKS {
+                    return Err(Error::InvalidFormat(format!(
+                        "{}: too many levels of symbolic links",
+                        path
+                    )));
+                }
+                let target = self.link_target(&inode)?;
+                if target.starts_with(b"/") {
+                    current = self.read_inode(self.sb.root_ino)?;
+                }
+                let target = String::from_utf8_lossy(&target).into_owned();
+                pending.extend(components(&target).rev());
+                continue;
+            }
+            current = inode;
+        }
+        Ok(current)
+    }
+
+    pub(super) fn link_target(&self, inode: &Inode) -> Result<Vec<u8>> {
+        let size = inode.size();
+        if size > MAX_PATH_LEN {
+            return Err(Error::InvalidFormat(format!(
+                "inode {}: symbolic link of {} bytes",
+                inode.ino, size
+            )));
+        }
+        if inode.format() == FORMAT_LOCAL {
+            return inode.local_data(size).map(<[u8]>::to_vec);
+        }
+        if !self.sb.v5() {
+            return self.read_data(inode);
+        }
+        // Each block of the target has a header of its own
+        let mut target = Vec::new();
+        for extent in self.extents(inode)? {
+            for i in 0..extent.len {
+                let block = self.read_fs_block(extent.fsb + i)?;
+                let len = be_u32(&block, 8) as usize;
+                if &block[0..4] != SYMLINK_MAGIC || SYMLINK_HEADER_SIZE + len > block.len() {
+                    return Err(Error::InvalidFormat(format!(
+                        "inode {}: corrupt symbolic link block",
+                        inode.ino
+                    )));
+                }
+                target.extend_from_slice(&block[SYMLINK_HEADER_SIZE..SYMLINK_HEADER_SIZE + len]);
+            }
+        }
+        target.truncate(size as usize);
+        Ok(target)
+    }
+}
+
+fn components(path: &str) -> impl DoubleEndedIterator<Item = Vec<u8>> + '_ {
+    path.split('/')
+        .filter(|name| !name.is_empty() && *name != ".")
+        .map(|name| name.as_bytes().to_vec())
+}
@@ -0,0 +1,364 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Inodes and the extent maps of their data forks
+
+use super::layout::{checksum_matches, Superblock};
+use super::Xfs;
+use crate::core::{Error, Result};
+
+const MAGIC: &[u8; 2] = b"IN";
+
+const S_IFMT: u16 = 0o170000;
+const S_IFLNK: u16 = 0o120000;
+const S_IFREG: u16 = 0o100000;
+const S_IFDIR: u16 = 0o040000;
+
+/// Data fork formats
+pub(super) const FORMAT_LOCAL: u8 = 1;
+const FORMAT_EXTENTS: u8 = 2;
+const FORMAT_BTREE: u8 = 3;
+
+/// The data is on the realtime device
+const DIFLAG_REALTIME: u16 = 0x1;
+const DIFLAG2_BIGTIME: u64 = 0x8;
+const DIFLAG2_NREXT64: u64 = 0x10;
+
+/// Size of the inode core before the forks, in v1/v2 and in v3 inodes
+const V2_CORE_SIZE: usize = 100;
+const V3_CORE_SIZE: usize = 176;
+const V3_CRC_OFFSET: usize = 100;
+
+/// Bigtime timestamps count nanoseconds from the smallest 32-bit time
+const BIGTIME_EPOCH_OFFSET: i64 = 1 << 31;
+const NSEC_PER_SEC: u64 = 1_000_000_000;
+
+/// Extent map btree blocks, with the short v4 header or the v5 one
+const BMAP_MAGIC: &[u8; 4] = b"BMAP";
+const BMA3_MAGIC: &[u8; 4] = b"BMA3";
+const BMAP_HEADER_SIZE: usize = 24;
+const BMA3_HEADER_SIZE: usize = 72;
+const BMA3_CRC_OFFSET: usize = 64;
+/// Deeper extent maps than this are corrupt
+const MAX_BTREE_LEVELS: u16 = 16;
+/// Size of an extent record, and of a btree key and pointer pair
+const RECORD_SIZE: usize = 16;
+
+fn be_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn be_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn be_u64(buf: &[u8], offset: usize) -> u64 {
+    u64::from_be_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
+
+/// File blocks `logical..logical + len` at filesystem blocks `fsb..`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(super) struct Extent {
+    pub logical: u64,
+    pub fsb: u64,
+    pub len: u64,
+    /// Preallocated and never written; reads as zeros
+    pub unwritten: bool,
+}
+
+impl Extent {
+    /// Unpack a 128-bit extent record: flag, 54-bit file offset, 52-bit
+    /// block and 21-bit length
+    pub fn parse(rec: &[u8]) -> Self {
+        let l0 = be_u64(rec, 0);
+        let l1 = be_u64(rec, 8);
+        Self {
+            logical: (l0 & ((1 << 63) - 1)) >> 9,
+            fsb: ((l0 & 0x1ff) << 43) | (l1 >> 21),
+            len: l1 & ((1 << 21) - 1),
+            unwritten: l0 >> 63 != 0,
+        }
+    }
+}
+
+pub(super) struct Inode {
+    pub ino: u64,
+    raw: Vec<u8>,
+}
+
+impl Inode {
+    pub fn parse(sb: &Superblock, ino: u64, raw: Vec<u8>) -> Result<Self> {
+        let inode = Self { ino, raw };
+        if &inode.raw[0..2] != MAGIC {
+            return Err(Error::InvalidFormat(format!("inode {}: bad magic", ino)));
+        }
+        let version = inode.version();
+        if !(1..=3).contains(&version) || (version == 3) != sb.v5() {
+            return Err(Error::InvalidFormat(format!(
+                "inode {}: version {}",
+                ino, version
+            )));
+        }
+        if version == 3
+            && (!checksum_matches(&inode.raw, V3_CRC_OFFSET) || be_u64(&inode.raw, 152) != ino)
+        {
+            return Err(Error::InvalidFormat(format!(
+                "inode {}: checksum mismatch",
+                ino
+            )));
+        }
+        if inode.fork_offset() > inode.raw.len() {
+            return Err(Error::InvalidFormat(format!(
+                "inode {}: bad fork offset",
+                ino
+            )));
+        }
+        Ok(inode)
+    }
+
+    fn version(&self) -> u8 {
+        self.raw[4]
+    }
+
+    fn core_size(&self) -> usize {
+        if self.version() == 3 {
+            V3_CORE_SIZE
+        } else {
+            V2_CORE_SIZE
+        }
+    }
+
+    /// Where the attribute fork starts, or the end of the inode
+    fn fork_offset(&self) -> usize {
+        match self.raw[82] {
+            0 => self.raw.len(),
+            forkoff => self.core_size() + usize::from(forkoff) * 8,
+        }
+    }
+
+    fn flags2(&self) -> u64 {
+        if self.version() == 3 {
+            be_u64(&self.raw, 120)
+        } else {
+            0
+        }
+    }
+
+    pub fn mode(&self) -> u16 {
+        be_u16(&self.raw, 2)
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.mode() & S_IFMT == S_IFDIR
+    }
+
+    pub fn is_reg(&self) -> bool {
+        self.mode() & S_IFMT == S_IFREG
+    }
+
+    pub fn is_symlink(&self) -> bool {
+        self.mode() & S_IFMT == S_IFLNK
+    }
+
+    pub fn format(&self) -> u8 {
+        self.raw[5]
+    }
+
+    pub fn uid(&self) -> u32 {
+        be_u32(&self.raw, 8)
+    }
+
+    pub fn gid(&self) -> u32 {
+        be_u32(&self.raw, 12)
+    }
+
+    pub fn links(&self) -> u32 {
+        match self.version() {
+            1 => u32::from(be_u16(&self.raw, 6)),
+            _ => be_u32(&self.raw, 16),
+        }
+    }
+
+    pub fn size(&self) -> u64 {
+        be_u64(&self.raw, 56)
+    }
+
+    /// Modification time in seconds since the Unix epoch
+    pub fn mtime(&self) -> i64 {
+        if self.flags2() & DIFLAG2_BIGTIME != 0 {
+            (be_u64(&self.raw, 40) / NSEC_PER_SEC) as i64 - BIGTIME_EPOCH_OFFSET
+        } else {
+            i64::from(be_u32(&self.raw, 40) as i32)
+        }
+    }
+
+    pub fn realtime(&self) -> bool {
+        be_u16(&self.raw, 90) & DIFLAG_REALTIME != 0
+    }
+
+    fn extent_count(&self) -> u64 {
+        if self.flags2() & DIFLAG2_NREXT64 != 0 {
+            be_u64(&self.raw, 24)
+        } else {
+            u64::from(be_u32(&self.raw, 76))
+        }
+    }
+
+    /// The data fork: inline data, extent records or the btree root
+    pub fn data_fork(&self) -> &[u8] {
+        &self.raw[self.core_size()..self.fork_offset()]
+    }
+
+    /// Inline data of `size` bytes
+    pub fn local_data(&self, size: u64) -> Result<&[u8]> {
+        let fork = self.data_fork();
+        if size > fork.len() as u64 {
+            return Err(Error::InvalidFormat(format!(
+                "inode {}: inline data larger than its fork",
+                self.ino
+            )));
+        }
+        Ok(&fork[..size as usize])
+    }
+}
+
+impl Xfs {
+    pub(super) fn read_inode(&self, ino: u64) -> Result<Inode> {
+        let mut raw = vec![0u8; self.sb.inode_size];
+        let offset = self.sb.inode_offset(ino)?;
+        self.volume.read_at(self.offset + offset, &mut raw)?;
+        Inode::parse(&self.sb, ino, raw)
+    }
+
+    /// Extents of the data fork of `inode`, in file order
+    pub(super) fn extents(&self, inode: &Inode) -> Result<Vec<Extent>> {
+        let fork = inode.data_fork();
+        let mut extents = Vec::new();
+        match inode.format() {
+            FORMAT_EXTENTS => {
+                let count = inode.extent_count();
+                if count > (fork.len() / RECORD_SIZE) as u64 {
+                    return Err(Error::InvalidFormat(format!(
+                        "inode {}: more extents than its fork holds",
+                        inode.ino
+                    )));
+                }
+                extents.extend(
+                    fork.chunks_exact(RECORD_SIZE)
+                        .take(count as usize)
+                        .map(Extent::parse),
+                );
+            }
+            FORMAT_BTREE => {
+                if fork.len() < 4 + RECORD_SIZE {
+                    return Err(Error::InvalidFormat(format!(
+                        "inode {}: corrupt extent btree root",
+                        inode.ino
+                    )));
+                }
+                let level = be_u16(fork, 0);
+                let count = usize::from(be_u16(fork, 2));
+                let max = (fork.len() - 4) / RECORD_SIZE;
+                if level == 0 || level > MAX_BTREE_LEVELS || count > max {
+                    return Err(Error::InvalidFormat(format!(
+                        "inode {}: corrupt extent btree root",
+                        inode.ino
+                    )));
+                }
+                for i in 0..count {
+                    let fsb = be_u64(fork, 4 + max * 8 + i * 8);
+                    self.btree_extents(inode.ino, fsb, level - 1, &mut extents)?;
+                }
+            }
+            format => {
+                return Err(Error::InvalidFormat(format!(
+                    "inode {}: data fork format {} has no extents",
+                    inode.ino, format
+                )))
+            }
+        }
+        Ok(extents)
+    }
+
+    fn btree_extents(&self, ino: u64, fsb: u64, level: u16, out: &mut Vec<Extent>) -> Result<()> {
+        let block = self.read_fs_block(fsb)?;
+        let (magic, header) = if self.sb.v5() {
+            (BMA3_MAGIC, BMA3_HEADER_SIZE)
+        } else {
+            (BMAP_MAGIC, BMAP_HEADER_SIZE)
+        };
+        let count = usize::from(be_u16(&block, 6));
+        let max = (block.len() - header) / RECORD_SIZE;
+        let corrupt = &block[0..4] != magic
+            || be_u16(&block, 4) != level
+            || count > max
+            || (self.sb.v5()
+                && (!checksum_matches(&block, BMA3_CRC_OFFSET) || be_u64(&block, 56) != ino));
+        if corrupt {
+            return Err(Error::InvalidFormat(format!(
+                "inode {}: corrupt extent btree block {}",
+                ino, fsb
+            )));
+        }
+        if level == 0 {
+            out.extend(
+                block[header..]
+                    .chunks_exact(RECORD_SIZE)
+                    .take(count)
+                    .map(Extent::parse),
+            );
+            return Ok(());
+        }
+        for i in 0..count {
+            let child = be_u64(&block, header + max * 8 + i * 8);
+            self.btree_extents(ino, child, level - 1, out)?;
+        }
+        Ok(())
+    }
+
+    /// Content of a regular file; holes and unwritten extents read as
+    /// zeros, blocks shared with reflinked copies as any other
+    pub(super) fn read_data(&self, inode: &Inode) -> Result<Vec<u8>> {
+        if inode.realtime() {
+            return Err(Error::Unsupported(format!(
+                "inode {}: files on the realtime device",
+                inode.ino
+            )));
+        }
+        let size = inode.size();
+        if inode.format() == FORMAT_LOCAL {
+            return inode.local_data(size).map(<[u8]>::to_vec);
+        }
+        let len = usize::try_from(size)
+            .map_err(|_| Error::ResourceLimit(format!("inode {}: {} bytes", inode.ino, size)))?;
+        let block_size = self.sb.block_size;
+        let mut data = vec![0u8; len];
+        for extent in self.extents(inode)?.iter().filter(|e| !e.unwritten) {
+            let start = extent.logical.saturating_mul(block_size);
+            if start >= size || extent.len == 0 {
+                continue;
+            }
+            let end = (start + extent.len * block_size).min(size);
+            // Extents never cross allocation groups, so are contiguous
+            let first = self.sb.disk_block(extent.fsb)?;
+            if self.sb.disk_block(extent.fsb + extent.len - 1)? != first + extent.len - 1 {
+                return Err(Error::InvalidFormat(format!(
+                    "inode {}: extent crosses allocation groups",
+                    inode.ino
+                )));
+            }
+            self.volume.read_at(
+                self.offset + first * block_size,
+                &mut data[start as usize..end as usize],
+            )?;
+        }
+        Ok(data)
+    }
+
+    /// Filesystem block `fsb`
+    pub(super) fn read_fs_block(&self, fsb: u64) -> Result<Vec<u8>> {
+        let block = self.sb.disk_block(fsb)?;
+        let mut data = vec![0u8; self.sb.block_size as usize];
+        self.volume
+            .read_at(self.offset + block * self.sb.block_size, &mut data)?;
+        Ok(data)
+    }
+}
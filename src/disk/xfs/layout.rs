@@ -0,0 +1,226 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Superblock, feature flags and metadata checksums
+
+use crate::core::{Error, Result};
+use crate::disk::vhdx::CRC32C_TABLE;
+
+/// The primary superblock is the first sector of allocation group 0
+pub(super) const SUPERBLOCK_SIZE: usize = 512;
+const MAGIC: &[u8; 4] = b"XFSB";
+
+const VERSION_NUMBITS: u16 = 0x000f;
+const VERSION_4: u16 = 4;
+const VERSION_5: u16 = 5;
+/// Version 2 directories; version 1 ones predate 2002
+const VERSION_DIRV2BIT: u16 = 0x2000;
+const VERSION_MOREBITSBIT: u16 = 0x8000;
+/// Directory entries record file types (v4)
+const VERSION2_FTYPE: u32 = 0x200;
+
+const INCOMPAT_FTYPE: u32 = 0x1;
+const INCOMPAT_SPINODES: u32 = 0x2;
+const INCOMPAT_META_UUID: u32 = 0x4;
+const INCOMPAT_BIGTIME: u32 = 0x8;
+const INCOMPAT_NEEDSREPAIR: u32 = 0x10;
+const INCOMPAT_NREXT64: u32 = 0x20;
+const INCOMPAT_EXCHRANGE: u32 = 0x40;
+const INCOMPAT_PARENT: u32 = 0x80;
+
+/// Incompatible features the reader understands; not the metadata
+/// directory tree or realtime groups
+const INCOMPAT_READ: u32 = INCOMPAT_FTYPE
+    | INCOMPAT_SPINODES
+    | INCOMPAT_META_UUID
+    | INCOMPAT_BIGTIME
+    | INCOMPAT_NEEDSREPAIR
+    | INCOMPAT_NREXT64
+    | INCOMPAT_EXCHRANGE
+    | INCOMPAT_PARENT;
+
+/// Offset of the superblock CRC
+pub(super) const SB_CRC_OFFSET: usize = 0xe0;
+
+fn be_u16(buf: &[u8], offset: usize) -> u16 {
+    u16::from_be_bytes([buf[offset], buf[offset + 1]])
+}
+
+fn be_u32(buf: &[u8], offset: usize) -> u32 {
+    u32::from_be_bytes(buf[offset..offset + 4].try_into().unwrap())
+}
+
+fn be_u64(buf: &[u8], offset: usize) -> u64 {
+    u64::from_be_bytes(buf[offset..offset + 8].try_into().unwrap())
+}
+
+/// CRC-32C as XFS stamps its metadata: over the whole structure with the
+/// little-endian checksum field at `field` zeroed
+pub(super) fn checksum_matches(buf: &[u8], field: usize) -> bool {
+    let stored = u32::from_le_bytes(buf[field..field + 4].try_into().unwrap());
+    let crc = buf.iter().enumerate().fold(!0u32, |crc, (i, &byte)| {
+        let byte = if (field..field + 4).contains(&i) {
+            0
+        } else {
+            byte
+        };
+        CRC32C_TABLE[((crc ^ u32::from(byte)) & 0xff) as usize] ^ (crc >> 8)
+    });
+    !crc == stored
+}
+
+pub(super) struct Superblock {
+    pub block_size: u64,
+    pub block_log: u32,
+    /// Blocks in the data device
+    pub blocks: u64,
+    pub uuid: [u8; 16],
+    pub root_ino: u64,
+    /// Blocks per allocation group, and the bits of that in block numbers
+    pub ag_blocks: u64,
+    pub ag_block_log: u32,
+    pub ag_count: u32,
+    pub sector_size: usize,
+    pub inode_size: usize,
+    pub inodes_per_block_log: u32,
+    pub dir_block_log: u32,
+    label: [u8; 12],
+    version: u16,
+    features2: u32,
+    incompat: u32,
+}
+
+impl Superblock {
+    pub fn parse(raw: &[u8]) -> Result<Self> {
+        if &raw[0..4] != MAGIC {
+            return Err(Error::InvalidFormat("not an XFS filesystem".to_string()));
+        }
+        let versionnum = be_u16(raw, 0x64);
+        let version = versionnum & VERSION_NUMBITS;
+        let v5 = version == VERSION_5;
+        let sb = Self {
+            block_size: u64::from(be_u32(raw, 0x4)),
+            block_log: u32::from(raw[0x78]),
+            blocks: be_u64(raw, 0x8),
+            uuid: raw[0x20..0x30].try_into().unwrap(),
+            root_ino: be_u64(raw, 0x38),
+            ag_blocks: u64::from(be_u32(raw, 0x54)),
+            ag_block_log: u32::from(raw[0x7c]),
+            ag_count: be_u32(raw, 0x58),
+            sector_size: usize::from(be_u16(raw, 0x66)),
+            inode_size: usize::from(be_u16(raw, 0x68)),
+            inodes_per_block_log: u32::from(raw[0x7b]),
+            dir_block_log: u32::from(raw[0xc0]),
+            label: raw[0x6c..0x78].try_into().unwrap(),
+            version,
+            features2: if versionnum & VERSION_MOREBITSBIT != 0 || v5 {
+                be_u32(raw, 0xc8)
+            } else {
+                0
+            },
+            incompat: if v5 { be_u32(raw, 0xd8) } else { 0 },
+        };
+
+        match version {
+            VERSION_5 => {}
+            VERSION_4 if versionnum & VERSION_DIRV2BIT != 0 => {}
+            _ => {
+                return Err(Error::Unsupported(format!(
+                    "XFS version {} filesystems",
+                    version
+                )))
+            }
+        }
+        let sane = sb.block_size.is_power_of_two()
+            && (512..=65536).contains(&sb.block_size)
+            && sb.block_size == 1 << sb.block_log
+            && sb.sector_size.is_power_of_two()
+            && (SUPERBLOCK_SIZE..=32768).contains(&sb.sector_size)
+            && sb.inode_size.is_power_of_two()
+            && (256..=2048).contains(&sb.inode_size)
+            && (sb.inode_size << sb.inodes_per_block_log) as u64 == sb.block_size
+            && sb.ag_count > 0
+            && sb.ag_blocks > 0
+            && sb.ag_block_log < 32
+            && sb.ag_blocks <= 1 << sb.ag_block_log
+            && sb.dir_block_log <= 16 - sb.block_log.min(16)
+            && raw[0x7e] == 0;
+        if !sane {
+            return Err(Error::InvalidFormat("corrupt XFS superblock".to_string()));
+        }
+        Ok(sb)
+    }
+
+    /// Check the superblock CRC of v5 filesystems, over its whole sector
+    pub fn verify(&self, sector: &[u8]) -> Result<()> {
+        if self.v5() && !checksum_matches(sector, SB_CRC_OFFSET) {
+            return Err(Error::InvalidFormat(
+                "XFS superblock checksum mismatch".to_string(),
+            ));
+        }
+        if self.incompat & INCOMPAT_NEEDSREPAIR != 0 {
+            return Err(Error::InvalidState(
+                "the filesystem needs xfs_repair".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Incompatible features the reader doesn't know
+    pub fn unreadable_features(&self) -> u32 {
+        self.incompat & !INCOMPAT_READ
+    }
+
+    /// 4 or 5; version 5 checksums its metadata
+    pub fn version(&self) -> u16 {
+        self.version
+    }
+
+    pub fn v5(&self) -> bool {
+        self.version == VERSION_5
+    }
+
+    /// Whether directory entries record file types
+    pub fn ftype(&self) -> bool {
+        if self.v5() {
+            self.incompat & INCOMPAT_FTYPE != 0
+        } else {
+            self.features2 & VERSION2_FTYPE != 0
+        }
+    }
+
+    pub fn label(&self) -> String {
+        let end = self.label.iter().position(|&b| b == 0).unwrap_or(12);
+        String::from_utf8_lossy(&self.label[..end]).into_owned()
+    }
+
+    pub fn dir_block_size(&self) -> u64 {
+        self.block_size << self.dir_block_log
+    }
+
+    /// Disk block of filesystem block `fsb`, which encodes the allocation
+    /// group in its high bits
+    pub fn disk_block(&self, fsb: u64) -> Result<u64> {
+        let ag = fsb >> self.ag_block_log;
+        let block = fsb & ((1 << self.ag_block_log) - 1);
+        let disk_block = ag * self.ag_blocks + block;
+        if ag >= u64::from(self.ag_count) || block >= self.ag_blocks || disk_block >= self.blocks {
+            return Err(Error::InvalidFormat(format!(
+                "block {} is outside the filesystem",
+                fsb
+            )));
+        }
+        Ok(disk_block)
+    }
+
+    /// Byte offset of inode `ino` in the filesystem
+    pub fn inode_offset(&self, ino: u64) -> Result<u64> {
+        let per_block_log = self.inodes_per_block_log;
+        let ag_ino_log = self.ag_block_log + per_block_log;
+        let ag = ino >> ag_ino_log;
+        let block = (ino & ((1 << ag_ino_log) - 1)) >> per_block_log;
+        let index = ino & ((1 << per_block_log) - 1);
+        if ino == 0 || ag >= u64::from(self.ag_count) || block >= self.ag_blocks {
+            return Err(Error::InvalidFormat(format!("bad inode number {}", ino)));
+        }
+        Ok((ag * self.ag_blocks + block) * self.block_size + index * self.inode_size as u64)
+    }
+}
@@ -0,0 +1,438 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Pure Rust XFS reader
+//!
+//! Reads XFS filesystems in disk images without mounting them, so guests
+//! installed on XFS (RHEL, CentOS, Rocky, Alma, Oracle Linux) can be
+//! inspected where libguestfs and its appliance are not available.
+//!
+//! Both v4 filesystems and the v5 ones mkfs.xfs makes by default since
+//! xfsprogs 3.2.3 are read: v5 superblock and inode checksums are
+//! verified, and reflinked extents, file types in directory entries,
+//! timestamps past 2038 (bigtime) and large extent counters are
+//! understood. Files on a realtime device and filesystems with a metadata
+//! directory tree are refused. The log isn't replayed, so a filesystem
+//! that wasn't cleanly unmounted reads as of its last checkpoint.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use guestkit::disk::Xfs;
+//!
+//! // The root filesystem of a raw image, in a partition at 1 MiB
+//! let fs = Xfs::open("/srv/images/rhel9.img", 1024 * 1024)?;
+//! let os_release = fs.read_file("/etc/os-release")?;
+//! for entry in fs.list_dir("/etc/yum.repos.d")? {
+//!     println!("{}", entry.name);
+//! }
+//! # Ok::<(), guestkit::Error>(())
+//! ```
+
+mod dir;
+mod inode;
+mod layout;
+
+use crate::core::{Error, Result};
+use crate::disk::DiskReader;
+use layout::{Superblock, SUPERBLOCK_SIZE};
+use serde::Serialize;
+use std::fs::File;
+use std::os::unix::fs::FileExt;
+use std::path::Path;
+use uuid::Uuid;
+
+/// File metadata, as `lstat` reports it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct XfsStat {
+    pub ino: u64,
+    /// File type and permission bits
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    pub links: u32,
+    pub mtime: i64,
+}
+
+/// A directory entry
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct XfsDirEntry {
+    pub name: String,
+    pub ino: u64,
+    /// Type from the entry: 1 regular file, 2 directory, 7 symbolic link,
+    /// 0 if the filesystem doesn't record it
+    pub file_type: u8,
+}
+
+/// Where the filesystem is: a raw image or device, or any image through
+/// the disk reader
+enum Volume {
+    File(File),
+    Reader(Box<DiskReader>),
+}
+
+impl Volume {
+    fn read_at(&self, offset: u64, buf: &mut [u8]) -> Result<()> {
+        match self {
+            Volume::File(file) => file.read_exact_at(buf, offset).map_err(Error::Io),
+            Volume::Reader(reader) => reader.pread_exact(offset, buf),
+        }
+    }
+}
+
+/// An XFS filesystem at `offset` of a disk image
+pub struct Xfs {
+    volume: Volume,
+    offset: u64,
+    sb: Superblock,
+}
+
+impl Xfs {
+    /// Open the filesystem at `offset` of a raw image or device
+    pub fn open<P: AsRef<Path>>(path: P, offset: u64) -> Result<Self> {
+        let file = File::open(path.as_ref())?;
+        Self::load(Volume::File(file), offset)
+    }
+
+    /// Read the filesystem at `offset` of any image the disk reader opens
+    pub fn from_reader(reader: DiskReader, offset: u64) -> Result<Self> {
+        Self::load(Volume::Reader(Box::new(reader)), offset)
+    }
+
+    fn load(volume: Volume, offset: u64) -> Result<Self> {
+        let mut raw = vec![0u8; SUPERBLOCK_SIZE];
+        volume.read_at(offset, &mut raw)?;
+        let sb = Superblock::parse(&raw)?;
+        if sb.sector_size > raw.len() {
+            raw.resize(sb.sector_size, 0);
+            volume.read_at(offset, &mut raw)?;
+        }
+        sb.verify(&raw)?;
+        let unreadable = sb.unreadable_features();
+        if unreadable != 0 {
+            return Err(Error::Unsupported(format!(
+                "XFS incompatible features 0x{:x}",
+                unreadable
+            )));
+        }
+        Ok(Self { volume, offset, sb })
+    }
+
+    pub fn label(&self) -> String {
+        self.sb.label()
+    }
+
+    pub fn uuid(&self) -> Uuid {
+        Uuid::from_bytes(self.sb.uuid)
+    }
+
+    pub fn block_size(&self) -> u64 {
+        self.sb.block_size
+    }
+
+    /// On-disk format version: 4, or 5 for filesystems with checksums
+    pub fn version(&self) -> u16 {
+        self.sb.version()
+    }
+
+    /// Metadata of `path`, not following a final symbolic link
+    pub fn stat(&self, path: &str) -> Result<XfsStat> {
+        let inode = self.resolve(path, false)?;
+        Ok(XfsStat {
+            ino: inode.ino,
+            mode: u32::from(inode.mode()),
+            uid: inode.uid(),
+            gid: inode.gid(),
+            size: inode.size(),
+            links: inode.links(),
+            mtime: inode.mtime(),
+        })
+    }
+
+    pub fn exists(&self, path: &str) -> Result<bool> {
+        match self.resolve(path, false) {
+            Ok(_) => Ok(true),
+            Err(Error::NotFound(_)) => Ok(false),
+            Err(e) => Err(e),
+        }
+    }
+
+    pub fn read_file(&self, path: &str) -> Result<Vec<u8>> {
+        let inode = self.resolve(path, true)?;
+        if !inode.is_reg() {
+            return Err(Error::InvalidOperation(format!(
+                "{}: not a regular file",
+                path
+            )));
+        }
+        self.read_data(&inode)
+    }
+
+    pub fn read_link(&self, path: &str) -> Result<String> {
+        let inode = self.resolve(path, false)?;
+        if !inode.is_symlink() {
+            return Err(Error::InvalidOperation(format!(
+                "{}: not a symbolic link",
+                path
+            )));
+        }
+        let target = self.link_target(&inode)?;
+        Ok(String::from_utf8_lossy(&target).into_owned())
+    }
+
+    /// Entries of directory `path`, "." and ".." included
+    pub fn list_dir(&self, path: &str) -> Result<Vec<XfsDirEntry>> {
+        let dir = self.resolve(path, true)?;
+        if !dir.is_dir() {
+            return Err(Error::NotFound(format!("{}: not a directory", path)));
+        }
+        Ok(self
+            .entries(&dir)?
+            .into_iter()
+            .map(|entry| XfsDirEntry {
+                name: String::from_utf8_lossy(&entry.name).into_owned(),
+                ino: entry.ino,
+                file_type: entry.file_type,
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::disk::vhdx::CRC32C_TABLE;
+    use std::io::Write;
+
+    const BLOCK: usize = 4096;
+    const INODE: usize = 512;
+    /// Inodes in block 8: 64 and on
+    const ROOT: u64 = 64;
+    const ETC: u64 = 65;
+    const HELLO: u64 = 66;
+    const OS_RELEASE: u64 = 67;
+    const LINK: u64 = 68;
+
+    fn stamp(buf: &mut [u8], field: usize) {
+        buf[field..field + 4].fill(0);
+        let crc = buf.iter().fold(!0u32, |crc, &byte| {
+            CRC32C_TABLE[((crc ^ u32::from(byte)) & 0xff) as usize] ^ (crc >> 8)
+        });
+        buf[field..field + 4].copy_from_slice(&(!crc).to_le_bytes());
+    }
+
+    fn extent(logical: u64, fsb: u64, len: u64, unwritten: bool) -> [u8; 16] {
+        let l0 = (u64::from(unwritten) << 63) | (logical << 9) | (fsb >> 43);
+        let l1 = (fsb << 21) | len;
+        let mut rec = [0u8; 16];
+        rec[..8].copy_from_slice(&l0.to_be_bytes());
+        rec[8..].copy_from_slice(&l1.to_be_bytes());
+        rec
+    }
+
+    /// A v3 inode with bigtime timestamps; `fork` is its data fork
+    fn inode(image: &mut [u8], ino: u64, mode: u16, format: u8, size: u64, fork: &[u8]) {
+        let at = 8 * BLOCK + (ino - ROOT) as usize * INODE;
+        let raw = &mut image[at..at + INODE];
+        raw[0..2].copy_from_slice(b"IN");
+        raw[2..4].copy_from_slice(&mode.to_be_bytes());
+        raw[4] = 3;
+        raw[5] = format;
+        raw[16..20].copy_from_slice(&1u32.to_be_bytes());
+        // 2040-01-01, past what 32-bit timestamps hold
+        let mtime = (2_208_988_800u64 + (1 << 31)) * 1_000_000_000;
+        raw[40..48].copy_from_slice(&mtime.to_be_bytes());
+        raw[56..64].copy_from_slice(&size.to_be_bytes());
+        if format == 2 {
+            raw[76..80].copy_from_slice(&((fork.len() / 16) as u32).to_be_bytes());
+        }
+        // Reflink and bigtime
+        raw[120..128].copy_from_slice(&0xau64.to_be_bytes());
+        raw[152..160].copy_from_slice(&ino.to_be_bytes());
+        raw[176..176 + fork.len()].copy_from_slice(fork);
+        stamp(raw, 100);
+    }
+
+    fn dir_entry(block: &mut Vec<u8>, ino: u64, name: &[u8], file_type: u8) {
+        let start = block.len();
+        block.extend_from_slice(&ino.to_be_bytes());
+        block.push(name.len() as u8);
+        block.extend_from_slice(name);
+        block.push(file_type);
+        let len = (8 + 1 + name.len() + 1 + 2 + 7) & !7;
+        block.resize(start + len - 2, 0);
+        block.extend_from_slice(&(start as u16).to_be_bytes());
+    }
+
+    /// A v5 filesystem of one 64-block allocation group, as mkfs.xfs
+    /// makes them today: checksums, file types, reflink and bigtime
+    fn image() -> Vec<u8> {
+        let mut image = vec![0u8; 64 * BLOCK];
+        let sb = &mut image[..512];
+        sb[0..4].copy_from_slice(b"XFSB");
+        sb[4..8].copy_from_slice(&(BLOCK as u32).to_be_bytes());
+        sb[8..16].copy_from_slice(&64u64.to_be_bytes());
+        sb[0x20..0x30].copy_from_slice(&[0x5a; 16]);
+        sb[0x38..0x40].copy_from_slice(&ROOT.to_be_bytes());
+        sb[0x54..0x58].copy_from_slice(&64u32.to_be_bytes());
+        sb[0x58..0x5c].copy_from_slice(&1u32.to_be_bytes());
+        sb[0x64..0x66].copy_from_slice(&0xb4a5u16.to_be_bytes());
+        sb[0x66..0x68].copy_from_slice(&512u16.to_be_bytes());
+        sb[0x68..0x6a].copy_from_slice(&(INODE as u16).to_be_bytes());
+        sb[0x6a..0x6c].copy_from_slice(&8u16.to_be_bytes());
+        sb[0x6c..0x72].copy_from_slice(b"rootfs");
+        sb[0x78] = 12;
+        sb[0x79] = 9;
+        sb[0x7a] = 9;
+        sb[0x7b] = 3;
+        sb[0x7c] = 6;
+        sb[0xd4..0xd8].copy_from_slice(&0x5u32.to_be_bytes());
+        sb[0xd8..0xdc].copy_from_slice(&0x9u32.to_be_bytes());
+        stamp(sb, 0xe0);
+
+        // Short form root: hello and etc
+        let mut root = vec![2, 0];
+        root.extend_from_slice(&(ROOT as u32).to_be_bytes());
+        for (offset, name, file_type, ino) in
+            [(0x60u16, &b"etc"[..], 2, ETC), (0x70, b"hello", 1, HELLO)]
+        {
+            root.push(name.len() as u8);
+            root.extend_from_slice(&offset.to_be_bytes());
+            root.extend_from_slice(name);
+            root.push(file_type);
+            root.extend_from_slice(&(ino as u32).to_be_bytes());
+        }
+        inode(&mut image, ROOT, 0o40755, 1, root.len() as u64, &root);
+
+        // A single block directory at block 16
+        let mut block = b"XDB3".to_vec();
+        block.resize(64, 0);
+        dir_entry(&mut block, ETC, b".", 2);
+        dir_entry(&mut block, ROOT, b"..", 2);
+        dir_entry(&mut block, OS_RELEASE, b"os-release", 1);
+        dir_entry(&mut block, LINK, b"hostname", 7);
+        let used = block.len();
+        block.extend_from_slice(&0xffffu16.to_be_bytes());
+        block.extend_from_slice(&((BLOCK - 8 - 4 * 8 - used) as u16).to_be_bytes());
+        block.resize(BLOCK - 8, 0);
+        block.extend_from_slice(&4u32.to_be_bytes());
+        block.extend_from_slice(&0u32.to_be_bytes());
+        block[40..48].copy_from_slice(&ETC.to_be_bytes());
+        stamp(&mut block, 4);
+        image[16 * BLOCK..17 * BLOCK].copy_from_slice(&block);
+        inode(
+            &mut image,
+            ETC,
+            0o40755,
+            2,
+            BLOCK as u64,
+            &extent(0, 16, 1, false),
+        );
+
+        // Two blocks, the second preallocated and unwritten
+        let os_release = b"NAME=\"Rocky Linux\"\nVERSION_ID=\"9.4\"\nID=\"rocky\"\n";
+        image[20 * BLOCK..20 * BLOCK + os_release.len()].copy_from_slice(os_release);
+        image[21 * BLOCK..22 * BLOCK].fill(0xee);
+        let mut fork = extent(0, 20, 1, false).to_vec();
+        fork.extend_from_slice(&extent(1, 21, 1, true));
+        inode(
+            &mut image,
+            OS_RELEASE,
+            0o100644,
+            2,
+            BLOCK as u64 + 10,
+            &fork,
+        );
+
+        // An extent btree whose root points at a leaf in block 30
+        let mut root = vec![0, 1, 0, 1];
+        root.resize(4 + 20 * 8, 0);
+        root.extend_from_slice(&30u64.to_be_bytes());
+        inode(&mut image, HELLO, 0o100600, 3, 6, &root);
+        let mut leaf = b"BMA3".to_vec();
+        leaf.extend_from_slice(&[0, 0, 0, 1]);
+        leaf.extend_from_slice(&[0xff; 16]);
+        leaf.resize(56, 0);
+        leaf.extend_from_slice(&HELLO.to_be_bytes());
+        leaf.resize(72, 0);
+        leaf.extend_from_slice(&extent(0, 31, 1, false));
+        leaf.resize(BLOCK, 0);
+        stamp(&mut leaf, 64);
+        image[30 * BLOCK..31 * BLOCK].copy_from_slice(&leaf);
+        image[31 * BLOCK..31 * BLOCK + 6].copy_from_slice(b"hello\n");
+
+        inode(&mut image, LINK, 0o120777, 1, 8, b"../hello");
+        image
+    }
+
+    fn open(image: &[u8]) -> (tempfile::NamedTempFile, Xfs) {
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(image).unwrap();
+        let fs = Xfs::open(file.path(), 0).unwrap();
+        (file, fs)
+    }
+
+    #[test]
+    fn test_read_v5() {
+        let (_file, fs) = open(&image());
+        assert_eq!(fs.version(), 5);
+        assert_eq!(fs.label(), "rootfs");
+        assert_eq!(fs.uuid(), Uuid::from_bytes([0x5a; 16]));
+
+        let names: Vec<_> = fs
+            .list_dir("/")
+            .unwrap()
+            .into_iter()
+            .map(|entry| (entry.name, entry.ino, entry.file_type))
+            .collect();
+        assert_eq!(
+            names,
+            [
+                (".".to_string(), ROOT, 2),
+                ("..".to_string(), ROOT, 2),
+                ("etc".to_string(), ETC, 2),
+                ("hello".to_string(), HELLO, 1),
+            ]
+        );
+        assert_eq!(fs.list_dir("/etc").unwrap().len(), 4);
+
+        let data = fs.read_file("/etc/os-release").unwrap();
+        assert!(data.starts_with(b"NAME=\"Rocky Linux\""));
+        assert_eq!(data.len(), BLOCK + 10);
+        assert!(data[BLOCK..].iter().all(|&b| b == 0));
+        assert_eq!(fs.read_file("/hello").unwrap(), b"hello\n");
+        assert_eq!(fs.read_link("/etc/hostname").unwrap(), "../hello");
+        assert_eq!(fs.read_file("/etc/hostname").unwrap(), b"hello\n");
+
+        let stat = fs.stat("/etc/os-release").unwrap();
+        assert_eq!(stat.mode, 0o100644);
+        assert_eq!(stat.mtime, 2_208_988_800);
+        assert!(fs.exists("/etc/../hello").unwrap());
+        assert!(!fs.exists("/etc/fstab").unwrap());
+    }
+
+    #[test]
+    fn test_rejects_bad_checksums_and_features() {
+        let mut bad_inode = image();
+        bad_inode[8 * BLOCK + INODE + 60] ^= 1;
+        let (_file, fs) = open(&bad_inode);
+        assert!(matches!(fs.list_dir("/etc"), Err(Error::InvalidFormat(_))));
+
+        let mut unknown = image();
+        // The metadata directory tree
+        unknown[0xda] |= 0x1;
+        stamp(&mut unknown[..512], 0xe0);
+        let mut file = tempfile::NamedTempFile::new().unwrap();
+        file.write_all(&unknown).unwrap();
+        assert!(matches!(
+            Xfs::open(file.path(), 0),
+            Err(Error::Unsupported(_))
+        ));
+
+        unknown[0x6c] = b'R';
+        file.as_file().write_all_at(&unknown[..512], 0).unwrap();
+        assert!(matches!(
+            Xfs::open(file.path(), 0),
+            Err(Error::InvalidFormat(_))
+        ));
+    }
+}
@@ -0,0 +1,390 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! libvirt domain XML and qemu-system command lines for disk images
+//!
+//! [`DomainSpec::for_image`] picks the firmware, disk bus and NIC model from
+//! the inspected [`ImageProperties`], so the guest boots with the drivers it
+//! has: virtio for Linux and for Windows with the virtio-win drivers,
+//! SATA and e1000 for Windows without them.
+
+use crate::core::escape::xml_escape;
+use crate::guestfs::{DiskBus, ImageProperties};
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// UEFI firmware images for direct QEMU boots, by distribution layout
+const OVMF_CODE: &[&str] = &[
+    "/usr/share/edk2/ovmf/OVMF_CODE.fd",
+    "/usr/share/OVMF/OVMF_CODE_4M.fd",
+    "/usr/share/OVMF/OVMF_CODE.fd",
+    "/usr/share/edk2/x64/OVMF_CODE.4m.fd",
+    "/usr/share/qemu/ovmf-x86_64-code.bin",
+];
+const AAVMF_CODE: &[&str] = &[
+    "/usr/share/edk2/aarch64/QEMU_EFI-pflash.raw",
+    "/usr/share/AAVMF/AAVMF_CODE.fd",
+    "/usr/share/qemu/aavmf-aarch64-code.bin",
+];
+
+/// Virtual machine to run a disk image in
+#[derive(Debug, Clone, Serialize)]
+pub struct DomainSpec {
+    pub name: String,
+    pub memory_mib: u64,
+    pub vcpus: u32,
+    /// "x86_64" or "aarch64"
+    pub arch: String,
+    pub uefi: bool,
+    /// Windows keeps the RTC in local time
+    pub localtime: bool,
+    pub disks: Vec<DomainDisk>,
+    pub nics: Vec<DomainNic>,
+}
+
+/// Disk attached to a domain
+#[derive(Debug, Clone, Serialize)]
+pub struct DomainDisk {
+    pub path: PathBuf,
+    /// Image format (qcow2, raw, vmdk, ...)
+    pub format: String,
+    pub bus: DiskBus,
+}
+
+/// Network adapter of a domain
+#[derive(Debug, Clone, Serialize)]
+pub struct DomainNic {
+    /// libvirt network; QEMU command lines use user-mode networking
+    pub network: String,
+    /// virtio, e1000, ...
+    pub model: String,
+    pub mac: Option<String>,
+}
+
+impl DomainSpec {
+    /// Domain booting `image` with one NIC on `network`
+    ///
+    /// Guests without a detected OS get virtio devices and BIOS firmware
+    /// unless the image has an ESP.
+    pub fn for_image(
+        name: &str,
+        image: &Path,
+        format: &str,
+        properties: &ImageProperties,
+        network: &str,
+    ) -> Self {
+        let windows = properties.os_type.as_deref() == Some("windows");
+        let bus = properties.disk_bus.unwrap_or(DiskBus::Virtio);
+        // Guests without virtio storage drivers lack virtio-net as well
+        let model = match bus {
+            DiskBus::Sata | DiskBus::Ide => "e1000",
+            DiskBus::Virtio | DiskBus::VirtioScsi => "virtio",
+        };
+        let arch = match properties.architecture.as_deref() {
+            Some("aarch64") => "aarch64",
+            _ => "x86_64",
+        };
+
+        Self {
+            name: name.to_string(),
+            memory_mib: if windows { 4096 } else { 2048 },
+            vcpus: 2,
+            arch: arch.to_string(),
+            // The aarch64 virt machine has no BIOS
+            uefi: properties.uefi || arch == "aarch64",
+            localtime: windows,
+            disks: vec![DomainDisk {
+                path: image.to_path_buf(),
+                format: format.to_string(),
+                bus,
+            }],
+            nics: vec![DomainNic {
+                network: network.to_string(),
+                model: model.to_string(),
+                mac: None,
+            }],
+        }
+    }
+
+    /// QEMU machine type; IDE disks need i440fx as q35 has no IDE controller
+    fn machine(&self) -> &'static str {
+        if self.arch == "aarch64" {
+            "virt"
+        } else if self.disks.iter().any(|disk| disk.bus == DiskBus::Ide) {
+            "pc"
+        } else {
+            "q35"
+        }
+    }
+
+    /// libvirt domain XML, ready for `virsh define`
+    pub fn to_libvirt_xml(&self) -> String {
+        let mut xml = String::from("<domain type='kvm'>\n");
+        xml.push_str(&format!("  <name>{}</name>\n", xml_escape(&self.name)));
+        xml.push_str(&format!(
+            "  <memory unit='MiB'>{}</memory>\n",
+            self.memory_mib
+        ));
+        xml.push_str(&format!("  <vcpu>{}</vcpu>\n", self.vcpus));
+        xml.push_str(if self.uefi {
+            "  <os firmware='efi'>\n"
+        } else {
+            "  <os>\n"
+        });
+        xml.push_str(&format!(
+            "    <type arch='{}' machine='{}'>hvm</type>\n",
+            self.arch,
+            self.machine()
+        ));
+        xml.push_str("  </os>\n");
+        xml.push_str("  <features>\n    <acpi/>\n");
+        if self.arch == "x86_64" {
+            xml.push_str("    <apic/>\n");
+        } else {
+            xml.push_str("    <gic version='host'/>\n");
+        }
+        xml.push_str("  </features>\n");
+        xml.push_str("  <cpu mode='host-passthrough'/>\n");
+        if self.localtime {
+            xml.push_str("  <clock offset='localtime'/>\n");
+        }
+        xml.push_str("  <devices>\n");
+
+        let mut used: Vec<(&str, u8)> = Vec::new();
+        for disk in &self.disks {
+            let (bus, prefix) = match disk.bus {
+                DiskBus::Virtio => ("virtio", "vd"),
+                DiskBus::VirtioScsi => ("scsi", "sd"),
+                DiskBus::Sata => ("sata", "sd"),
+                DiskBus::Ide => ("ide", "hd"),
+            };
+            // SCSI and SATA disks share the sdX names
+            let index = match used.iter_mut().find(|(p, _)| *p == prefix) {
+                Some((_, next)) => {
+                    *next += 1;
+                    *next
+                }
+                None => {
+                    used.push((prefix, 0));
+                    0
+                }
+            };
+            xml.push_str("    <disk type='file' device='disk'>\n");
+            xml.push_str(&format!(
+                "      <driver name='qemu' type='{}'/>\n",
+                xml_escape(&disk.format)
+            ));
+            xml.push_str(&format!(
+                "      <source file='{}'/>\n",
+                xml_escape(&disk.path.to_string_lossy())
+            ));
+            xml.push_str(&format!(
+                "      <target dev='{}{}' bus='{}'/>\n",
+                prefix,
+                (b'a' + index) as char,
+                bus
+            ));
+            xml.push_str("    </disk>\n");
+        }
+        if self
+            .disks
+            .iter()
+            .any(|disk| disk.bus == DiskBus::VirtioScsi)
+        {
+            xml.push_str("    <controller type='scsi' model='virtio-scsi'/>\n");
+        }
+
+        for nic in &self.nics {
+            xml.push_str("    <interface type='network'>\n");
+            if let Some(mac) = &nic.mac {
+                xml.push_str(&format!("      <mac address='{}'/>\n", xml_escape(mac)));
+            }
+            xml.push_str(&format!(
+                "      <source network='{}'/>\n",
+                xml_escape(&nic.network)
+            ));
+            xml.push_str(&format!(
+                "      <model type='{}'/>\n",
+                xml_escape(&nic.model)
+            ));
+            xml.push_str("    </interface>\n");
+        }
+
+        xml.push_str("    <console type='pty'/>\n");
+        xml.push_str("    <graphics type='vnc' autoport='yes'/>\n");
+        xml.push_str("  </devices>\n");
+        xml.push_str("</domain>\n");
+        xml
+    }
+
+    /// qemu-system command line, program first
+    ///
+    /// UEFI guests boot the first firmware image found on this host from
+    /// the usual distribution paths. Without a writable variable store,
+    /// boot entries the guest adds are lost at power-off.
+    pub fn to_qemu_args(&self) -> Vec<String> {
+        let mut args = vec![
+            format!("qemu-system-{}", self.arch),
+            "-name".to_string(),
+            self.name.clone(),
+            "-machine".to_string(),
+            format!("{},accel=kvm", self.machine()),
+            "-cpu".to_string(),
+            "host".to_string(),
+            "-smp".to_string(),
+            self.vcpus.to_string(),
+            "-m".to_string(),
+            format!("{}M", self.memory_mib),
+        ];
+        if self.uefi {
+            let candidates = if self.arch == "aarch64" {
+                AAVMF_CODE
+            } else {
+                OVMF_CODE
+            };
+            let firmware = candidates
+                .iter()
+                .find(|path| Path::new(path).exists())
+                .unwrap_or(&candidates[0]);
+            args.push("-drive".to_string());
+            args.push(format!(
+                "if=pflash,format=raw,readonly=on,file={}",
+                firmware
+            ));
+        }
+        if self.localtime {
+            args.push("-rtc".to_string());
+            args.push("base=localtime".to_string());
+        }
+
+        if self
+            .disks
+            .iter()
+            .any(|disk| disk.bus == DiskBus::VirtioScsi)
+        {
+            args.push("-device".to_string());
+            args.push("virtio-scsi-pci,id=scsi0".to_string());
+        }
+        for (index, disk) in self.disks.iter().enumerate() {
+            // Commas in -drive values are escaped by doubling them
+            let file = disk.path.to_string_lossy().replace(',', ",,");
+            args.push("-drive".to_string());
+            match disk.bus {
+                DiskBus::Virtio => {
+                    args.push(format!("file={},format={},if=virtio", file, disk.format))
+                }
+                DiskBus::Ide => args.push(format!("file={},format={},if=ide", file, disk.format)),
+                DiskBus::VirtioScsi | DiskBus::Sata => {
+                    args.push(format!(
+                        "file={},format={},if=none,id=disk{}",
+                        file, disk.format, index
+                    ));
+                    args.push("-device".to_string());
+                    args.push(if disk.bus == DiskBus::Sata {
+                        // q35's built-in AHCI controller
+                        format!("ide-hd,drive=disk{},bus=ide.{}", index, index)
+                    } else {
+                        format!("scsi-hd,drive=disk{},bus=scsi0.0", index)
+                    });
+                }
+            }
+        }
+
+        for (index, nic) in self.nics.iter().enumerate() {
+            let device = match nic.model.as_str() {
+                "virtio" => "virtio-net-pci",
+                model => model,
+            };
+            args.push("-netdev".to_string());
+            args.push(format!("user,id=net{}", index));
+            args.push("-device".to_string());
+            args.push(match &nic.mac {
+                Some(mac) => format!("{},netdev=net{},mac={}", device, index, mac),
+                None => format!("{},netdev=net{}", device, index),
+            });
+        }
+        args
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn properties(os_type: &str, disk_bus: Option<DiskBus>, uefi: bool) -> ImageProperties {
+        ImageProperties {
+            os_type: Some(os_type.to_string()),
+            architecture: Some("x86_64".to_string()),
+            disk_bus,
+            uefi,
+            ..ImageProperties::default()
+        }
+    }
+
+    #[test]
+    fn test_linux_uefi_domain() {
+        let spec = DomainSpec::for_image(
+            "web",
+            Path::new("/srv/images/web.qcow2"),
+            "qcow2",
+            &properties("linux", Some(DiskBus::Virtio), true),
+            "default",
+        );
+        let xml = spec.to_libvirt_xml();
+        assert!(xml.contains("<os firmware='efi'>"));
+        assert!(xml.contains("machine='q35'"));
+        assert!(xml.contains("<source file='/srv/images/web.qcow2'/>"));
+        assert!(xml.contains("<target dev='vda' bus='virtio'/>"));
+        assert!(xml.contains("<model type='virtio'/>"));
+        assert!(!xml.contains("localtime"));
+
+        let args = spec.to_qemu_args();
+        assert_eq!(args[0], "qemu-system-x86_64");
+        assert!(args.iter().any(|a| a.starts_with("if=pflash")));
+        assert!(args.contains(&"file=/srv/images/web.qcow2,format=qcow2,if=virtio".to_string()));
+        assert!(args.contains(&"virtio-net-pci,netdev=net0".to_string()));
+    }
+
+    #[test]
+    fn test_windows_without_virtio_drivers() {
+        let spec = DomainSpec::for_image(
+            "dc01",
+            Path::new("/srv/dc01,old.qcow2"),
+            "qcow2",
+            &properties("windows", Some(DiskBus::Sata), false),
+            "lan",
+        );
+        assert_eq!(spec.memory_mib, 4096);
+        let xml = spec.to_libvirt_xml();
+        assert!(xml.contains("<os>\n"));
+        assert!(xml.contains("<target dev='sda' bus='sata'/>"));
+        assert!(xml.contains("<model type='e1000'/>"));
+        assert!(xml.contains("<source network='lan'/>"));
+        assert!(xml.contains("<clock offset='localtime'/>"));
+
+        let args = spec.to_qemu_args();
+        assert!(
+            args.contains(&"file=/srv/dc01,,old.qcow2,format=qcow2,if=none,id=disk0".to_string())
+        );
+        assert!(args.contains(&"ide-hd,drive=disk0,bus=ide.0".to_string()));
+        assert!(args.contains(&"e1000,netdev=net0".to_string()));
+        assert!(args.contains(&"base=localtime".to_string()));
+    }
+
+    #[test]
+    fn test_virtio_scsi_and_ide() {
+        let mut spec = DomainSpec::for_image(
+            "app",
+            Path::new("app.raw"),
+            "raw",
+            &properties("windows", Some(DiskBus::VirtioScsi), false),
+            "default",
+        );
+        let xml = spec.to_libvirt_xml();
+        assert!(xml.contains("<target dev='sda' bus='scsi'/>"));
+        assert!(xml.contains("<controller type='scsi' model='virtio-scsi'/>"));
+        assert!(xml.contains("<model type='virtio'/>"));
+
+        spec.disks[0].bus = DiskBus::Ide;
+        assert!(spec.to_libvirt_xml().contains("machine='pc'"));
+        assert!(spec.to_qemu_args().contains(&"pc,accel=kvm".to_string()));
+    }
+}
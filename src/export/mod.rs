@@ -2,12 +2,15 @@
 //! Export module for generating reports in various formats
 //!
 //! This module provides functionality to export inspection results
-//! to different formats including HTML, PDF, and Markdown.
+//! to different formats including HTML, PDF, and Markdown, and to
+//! describe an image as a libvirt domain or QEMU command line.
 
+pub mod domain;
 pub mod html;
 pub mod pdf;
 pub mod template;
 
+pub use domain::{DomainDisk, DomainNic, DomainSpec};
 pub use html::{HtmlExporter, HtmlExportOptions};
 pub use pdf::{PdfExporter, PdfExportOptions, PaperSize};
 pub use template::{TemplateEngine, TemplateFormat, TemplateLevel, create_variable_map};
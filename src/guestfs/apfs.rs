@@ -0,0 +1,86 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! APFS operations for disk image manipulation
+//!
+//! Reads the APFS containers of macOS guests with the pure Rust reader;
+//! nothing is launched or mounted.
+
+use crate::core::Result;
+use crate::disk::{Apfs, ApfsDirEntry, ApfsVolume};
+use crate::guestfs::Guestfs;
+use std::path::Path;
+
+impl Guestfs {
+    /// Open the APFS container on `device` of the first drive with the pure
+    /// Rust reader
+    pub fn apfs_open(&self, device: &str) -> Result<Apfs> {
+        let (reader, offset) = self.open_partition(device)?;
+        Apfs::from_reader(reader, offset)
+    }
+
+    /// List the volumes of the APFS container on `device`
+    ///
+    pub fn apfs_volumes(&mut self, device: &str) -> Result<Vec<ApfsVolume>> {
+        if self.verbose {
+            eprintln!("guestfs: apfs_volumes {}", device);
+        }
+
+        self.apfs_open(device)?.volumes()
+    }
+
+    /// List directory `path` of volume `volume` of the APFS container on
+    /// `device`
+    ///
+    pub fn apfs_ls(
+        &mut self,
+        device: &str,
+        volume: usize,
+        path: &str,
+    ) -> Result<Vec<ApfsDirEntry>> {
+        if self.verbose {
+            eprintln!("guestfs: apfs_ls {} {} {}", device, volume, path);
+        }
+
+        self.apfs_open(device)?.volume(volume)?.list_dir(path)
+    }
+
+    /// Read a file of volume `volume` of the APFS container on `device`,
+    /// decompressed if macOS stored it compressed
+    ///
+    pub fn apfs_read_file(&mut self, device: &str, volume: usize, path: &str) -> Result<Vec<u8>> {
+        if self.verbose {
+            eprintln!("guestfs: apfs_read_file {} {} {}", device, volume, path);
+        }
+
+        self.apfs_open(device)?.volume(volume)?.read_file(path)
+    }
+
+    /// Copy a file of volume `volume` of the APFS container on `device` to
+    /// `filename` on the host
+    ///
+    pub fn apfs_download<P: AsRef<Path>>(
+        &mut self,
+        device: &str,
+        volume: usize,
+        path: &str,
+        filename: P,
+    ) -> Result<()> {
+        let content = self.apfs_read_file(device, volume, path)?;
+        std::fs::write(filename, content)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Error;
+
+    #[test]
+    fn test_apfs_volumes_needs_a_drive() {
+        let mut g = Guestfs::new().unwrap();
+        assert!(matches!(
+            g.apfs_volumes("/dev/sda2"),
+            Err(Error::InvalidState(_))
+        ));
+    }
+}
@@ -0,0 +1,195 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! SVR4 `newc` cpio writer, the format the kernel unpacks initramfs from
+//!
+//! Each entry is a 110-byte ASCII header of hex fields, the NUL-terminated
+//! name and the data, each padded to four bytes. Inode numbers count up
+//! from 1 in archive order rather than coming from the host.
+
+use super::walk::{Entry, Kind};
+use crate::core::{Error, Result};
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
+
+const MAGIC: &str = "070701";
+const TRAILER: &[u8] = b"TRAILER!!!";
+
+const S_IFIFO: u32 = 0o010000;
+const S_IFCHR: u32 = 0o020000;
+const S_IFDIR: u32 = 0o040000;
+const S_IFBLK: u32 = 0o060000;
+const S_IFREG: u32 = 0o100000;
+const S_IFLNK: u32 = 0o120000;
+
+/// Header fields after the magic, in order
+struct Header {
+    ino: u32,
+    mode: u32,
+    uid: u32,
+    gid: u32,
+    nlink: u32,
+    mtime: u32,
+    filesize: u32,
+    rdevmajor: u32,
+    rdevminor: u32,
+}
+
+pub(super) fn write<W: Write>(entries: &[Entry], mut out: W) -> Result<W> {
+    for (index, entry) in entries.iter().enumerate() {
+        let (file_type, nlink, (rdevmajor, rdevminor)) = match entry.kind {
+            Kind::File { .. } => (S_IFREG, 1, (0, 0)),
+            Kind::Dir => (S_IFDIR, 2, (0, 0)),
+            Kind::Symlink(_) => (S_IFLNK, 1, (0, 0)),
+            Kind::CharDevice { major, minor } => (S_IFCHR, 1, (major, minor)),
+            Kind::BlockDevice { major, minor } => (S_IFBLK, 1, (major, minor)),
+            Kind::Fifo => (S_IFIFO, 1, (0, 0)),
+        };
+        let filesize = match &entry.kind {
+            Kind::Symlink(target) => target.as_os_str().len() as u64,
+            _ => entry.size(),
+        };
+        let header = Header {
+            ino: index as u32 + 1,
+            mode: file_type | entry.mode,
+            uid: entry.uid,
+            gid: entry.gid,
+            nlink,
+            mtime: entry.mtime.clamp(0, i64::from(u32::MAX)) as u32,
+            filesize: u32::try_from(filesize).map_err(|_| {
+                Error::Unsupported(format!(
+                    "cpio cannot hold {}: files must be under 4 GiB",
+                    entry.path.display()
+                ))
+            })?,
+            rdevmajor,
+            rdevminor,
+        };
+        write_header(&mut out, &header, entry.path.as_os_str().as_bytes())?;
+
+        match &entry.kind {
+            Kind::File { size } => {
+                io::copy(&mut entry.data()?, &mut out).map_err(Error::Io)?;
+                pad(&mut out, *size)?;
+            }
+            Kind::Symlink(target) => {
+                out.write_all(target.as_os_str().as_bytes())
+                    .map_err(Error::Io)?;
+                pad(&mut out, filesize)?;
+            }
+            _ => {}
+        }
+    }
+
+    let trailer = Header {
+        ino: 0,
+        mode: 0,
+        uid: 0,
+        gid: 0,
+        nlink: 1,
+        mtime: 0,
+        filesize: 0,
+        rdevmajor: 0,
+        rdevminor: 0,
+    };
+    write_header(&mut out, &trailer, TRAILER)?;
+
+    Ok(out)
+}
+
+fn write_header<W: Write>(out: &mut W, header: &Header, name: &[u8]) -> Result<()> {
+    // devmajor and devminor of the archived filesystem, then the checksum,
+    // are always zero
+    let fields = [
+        header.ino,
+        header.mode,
+        header.uid,
+        header.gid,
+        header.nlink,
+        header.mtime,
+        header.filesize,
+        0,
+        0,
+        header.rdevmajor,
+        header.rdevminor,
+        name.len() as u32 + 1,
+        0,
+    ];
+    let mut raw = MAGIC.to_string();
+    for field in fields {
+        raw.push_str(&format!("{:08x}", field));
+    }
+    out.write_all(raw.as_bytes()).map_err(Error::Io)?;
+    out.write_all(name).map_err(Error::Io)?;
+    out.write_all(&[0]).map_err(Error::Io)?;
+    pad(out, (raw.len() + name.len() + 1) as u64)
+}
+
+/// Pad what followed the last four-byte boundary, `len` bytes, to the next
+fn pad<W: Write>(out: &mut W, len: u64) -> Result<()> {
+    let padding = (4 - len % 4) % 4;
+    out.write_all(&[0; 3][..padding as usize])
+        .map_err(Error::Io)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_write() {
+        let tmp = tempfile::tempdir().unwrap();
+        let init = tmp.path().join("init");
+        std::fs::write(&init, "#!/bin/sh\n").unwrap();
+
+        let entries = [
+            Entry {
+                path: PathBuf::from("dev"),
+                kind: Kind::Dir,
+                mode: 0o755,
+                uid: 0,
+                gid: 0,
+                mtime: 0,
+                source: tmp.path().to_path_buf(),
+            },
+            Entry {
+                path: PathBuf::from("dev/console"),
+                kind: Kind::CharDevice { major: 5, minor: 1 },
+                mode: 0o600,
+                uid: 0,
+                gid: 0,
+                mtime: 0,
+                source: tmp.path().to_path_buf(),
+            },
+            Entry {
+                path: PathBuf::from("init"),
+                kind: Kind::File { size: 10 },
+                mode: 0o755,
+                uid: 0,
+                gid: 0,
+                mtime: 0x10,
+                source: init,
+            },
+        ];
+        let bytes = write(&entries, Vec::new()).unwrap();
+
+        let dev = [
+            MAGIC, "00000001", "000041ed", "00000000", "00000000", "00000002", "00000000",
+            "00000000", "00000000", "00000000", "00000000", "00000000", "00000004", "00000000",
+        ]
+        .concat();
+        assert_eq!(&bytes[..110], dev.as_bytes());
+        assert_eq!(&bytes[110..116], b"dev\0\0\0");
+
+        // Headers and data start on four-byte boundaries
+        let console = &bytes[116..240];
+        assert_eq!(&console[78..94], b"0000000500000001");
+        assert_eq!(&console[110..124], b"dev/console\0\0\0");
+
+        let init = &bytes[240..];
+        assert_eq!(&init[110..116], b"init\0\0");
+        assert_eq!(&init[116..126], b"#!/bin/sh\n");
+        assert_eq!(&init[128..134], MAGIC.as_bytes());
+        assert_eq!(&init[128 + 110..128 + 121], b"TRAILER!!!\0");
+        assert_eq!(bytes.len() % 4, 0);
+    }
+}
@@ -1,12 +1,157 @@
 // SPDX-License-Identifier: LGPL-3.0-or-later
-//! Archive operations (tar, tgz, cpio)
+//! Archive operations (tar, tgz, cpio, zip)
 //!
-//! This implementation uses system tar command with mounted filesystems.
+//! `tar_in`/`tar_out` and friends run the system tar and cpio on the mounted
+//! filesystems. [`Guestfs::archive_out`] writes tar (plain, gzip or zstd),
+//! newc cpio and zip archives itself, selecting paths with include and
+//! exclude globs and producing the same bytes for the same tree:
+//!
+//! - `*` and `?` match within a path component, `**` across components
+//! - A glob containing `/` is anchored at the archived directory, one
+//!   without matches a name at any depth
+//! - Excluding a directory excludes everything below it; including one
+//!   includes everything below it, along with the directories leading to it
 
 use crate::core::{Error, Result};
 use crate::guestfs::Guestfs;
+use std::fmt;
+use std::fs::File;
+use std::io::{BufWriter, Write};
 use std::path::Path;
 use std::process::Command;
+use std::str::FromStr;
+
+mod cpio;
+mod tarball;
+mod walk;
+mod zip;
+
+/// Formats [`Guestfs::archive_out`] writes
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Tar,
+    TarGz,
+    TarZst,
+    /// SVR4 `newc` cpio, the format of initramfs images
+    Cpio,
+    Zip,
+}
+
+impl ArchiveFormat {
+    const EXTENSIONS: [(&'static str, ArchiveFormat); 7] = [
+        (".tar.gz", ArchiveFormat::TarGz),
+        (".tgz", ArchiveFormat::TarGz),
+        (".tar.zst", ArchiveFormat::TarZst),
+        (".tzst", ArchiveFormat::TarZst),
+        (".tar", ArchiveFormat::Tar),
+        (".cpio", ArchiveFormat::Cpio),
+        (".zip", ArchiveFormat::Zip),
+    ];
+
+    /// Format an output file's extension names
+    pub fn from_path(path: &Path) -> Option<Self> {
+        let name = path.file_name()?.to_str()?.to_ascii_lowercase();
+        Self::EXTENSIONS
+            .iter()
+            .find(|(extension, _)| name.ends_with(extension))
+            .map(|&(_, format)| format)
+    }
+}
+
+impl FromStr for ArchiveFormat {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "tar" => Ok(ArchiveFormat::Tar),
+            "tar.gz" | "tgz" | "gzip" => Ok(ArchiveFormat::TarGz),
+            "tar.zst" | "tzst" | "zstd" => Ok(ArchiveFormat::TarZst),
+            "cpio" | "newc" => Ok(ArchiveFormat::Cpio),
+            "zip" => Ok(ArchiveFormat::Zip),
+            _ => Err(Error::InvalidFormat(format!(
+                "Unknown archive format '{}' (tar, tar.gz, tar.zst, cpio, zip)",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for ArchiveFormat {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ArchiveFormat::Tar => "tar",
+            ArchiveFormat::TarGz => "tar.gz",
+            ArchiveFormat::TarZst => "tar.zst",
+            ArchiveFormat::Cpio => "cpio",
+            ArchiveFormat::Zip => "zip",
+        })
+    }
+}
+
+/// What [`Guestfs::archive_out`] puts in the archive
+#[derive(Debug, Clone, Default)]
+pub struct ArchiveOptions {
+    /// Only archive paths matching these globs
+    pub include: Vec<String>,
+    /// Leave out paths matching these globs
+    pub exclude: Vec<String>,
+    /// Clamp modification times to this many seconds since the epoch, as
+    /// `SOURCE_DATE_EPOCH` does
+    pub mtime: Option<i64>,
+    /// Compression level; the codec's default when unset
+    pub level: Option<i32>,
+}
+
+/// Options for [`Guestfs::tar_in_opts`] and [`Guestfs::tar_out_opts`]
+#[derive(Debug, Clone, Default)]
+pub struct TarOptions {
+    /// gzip, bzip2, xz or compress; uncompressed when unset
+    pub compress: Option<String>,
+    /// Store numeric user and group IDs instead of names (`tar_out_opts` only)
+    pub numeric_owner: bool,
+    pub xattrs: bool,
+    pub selinux: bool,
+    pub acls: bool,
+}
+
+impl TarOptions {
+    /// Append the compression and attribute flags to a tar command
+    fn apply(&self, cmd: &mut Command) {
+        match self.compress.as_deref() {
+            Some("gzip") | Some("gz") => {
+                cmd.arg("-z");
+            }
+            Some("bzip2") | Some("bz2") => {
+                cmd.arg("-j");
+            }
+            Some("xz") => {
+                cmd.arg("-J");
+            }
+            Some("compress") => {
+                cmd.arg("-Z");
+            }
+            _ => {}
+        }
+
+        if self.xattrs {
+            cmd.arg("--xattrs");
+        }
+        if self.selinux {
+            cmd.arg("--selinux");
+        }
+        if self.acls {
+            cmd.arg("--acls");
+        }
+    }
+}
+
+/// What [`Guestfs::archive_out`] wrote
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ArchiveSummary {
+    pub entries: usize,
+    /// Total size of the archived files, before compression
+    pub bytes: u64,
+}
 
 impl Guestfs {
     /// Extract tar archive into directory
@@ -259,10 +404,7 @@ impl Guestfs {
         &mut self,
         tarfile: P,
         directory: &str,
-        compress: Option<&str>,
-        xattrs: bool,
-        selinux: bool,
-        acls: bool,
+        options: &TarOptions,
     ) -> Result<()> {
         self.ensure_ready()?;
 
@@ -270,13 +412,10 @@ impl Guestfs {
 
         if self.verbose {
             eprintln!(
-                "guestfs: tar_in_opts {} {} compress={:?} xattrs={} selinux={} acls={}",
+                "guestfs: tar_in_opts {} {} {:?}",
                 tarfile.display(),
                 directory,
-                compress,
-                xattrs,
-                selinux,
-                acls
+                options
             );
         }
 
@@ -297,38 +436,10 @@ impl Guestfs {
 
         // Build tar command with options
         let mut cmd = Command::new("tar");
-
-        // Add compression flag if specified
-        match compress {
-            Some("gzip") | Some("gz") => {
-                cmd.arg("-z");
-            }
-            Some("bzip2") | Some("bz2") => {
-                cmd.arg("-j");
-            }
-            Some("xz") => {
-                cmd.arg("-J");
-            }
-            Some("compress") => {
-                cmd.arg("-Z");
-            }
-            _ => {}
-        }
-
+        options.apply(&mut cmd);
         cmd.arg("-xf").arg(tarfile);
         cmd.arg("-C").arg(&target_path);
 
-        // Add extended attributes options
-        if xattrs {
-            cmd.arg("--xattrs");
-        }
-        if selinux {
-            cmd.arg("--selinux");
-        }
-        if acls {
-            cmd.arg("--acls");
-        }
-
         let output = cmd
             .output()
             .map_err(|e| Error::CommandFailed(format!("Failed to run tar: {}", e)))?;
@@ -350,11 +461,7 @@ impl Guestfs {
         &mut self,
         directory: &str,
         tarfile: P,
-        compress: Option<&str>,
-        _numericowner: bool,
-        xattrs: bool,
-        selinux: bool,
-        acls: bool,
+        options: &TarOptions,
     ) -> Result<()> {
         self.ensure_ready()?;
 
@@ -362,13 +469,10 @@ impl Guestfs {
 
         if self.verbose {
             eprintln!(
-                "guestfs: tar_out_opts {} {} compress={:?} xattrs={} selinux={} acls={}",
+                "guestfs: tar_out_opts {} {} {:?}",
                 directory,
                 tarfile.display(),
-                compress,
-                xattrs,
-                selinux,
-                acls
+                options
             );
         }
 
@@ -397,38 +501,13 @@ impl Guestfs {
 
         // Build tar command with options
         let mut cmd = Command::new("tar");
-
-        // Add compression flag if specified
-        match compress {
-            Some("gzip") | Some("gz") => {
-                cmd.arg("-z");
-            }
-            Some("bzip2") | Some("bz2") => {
-                cmd.arg("-j");
-            }
-            Some("xz") => {
-                cmd.arg("-J");
-            }
-            Some("compress") => {
-                cmd.arg("-Z");
-            }
-            _ => {}
+        options.apply(&mut cmd);
+        if options.numeric_owner {
+            cmd.arg("--numeric-owner");
         }
-
         cmd.arg("-cf").arg(tarfile);
         cmd.arg("-C").arg(&source_path);
 
-        // Add extended attributes options
-        if xattrs {
-            cmd.arg("--xattrs");
-        }
-        if selinux {
-            cmd.arg("--selinux");
-        }
-        if acls {
-            cmd.arg("--acls");
-        }
-
         cmd.arg(".");
 
         let output = cmd
@@ -625,6 +704,76 @@ impl Guestfs {
 
         Ok(())
     }
+
+    /// Write `directory` to an archive on the host
+    ///
+    /// Entries are sorted by name and carry numeric owners and modification
+    /// times only, so the same tree always gives the same bytes. Zip
+    /// archives leave out device nodes and FIFOs.
+    pub fn archive_out<P: AsRef<Path>>(
+        &mut self,
+        directory: &str,
+        archive: P,
+        format: ArchiveFormat,
+        options: &ArchiveOptions,
+    ) -> Result<ArchiveSummary> {
+        self.ensure_ready()?;
+
+        let archive = archive.as_ref();
+
+        if self.verbose {
+            eprintln!(
+                "guestfs: archive_out {} {} format={}",
+                directory,
+                archive.display(),
+                format
+            );
+        }
+
+        let source_path = self.resolve_guest_path(directory)?;
+        if !source_path.is_dir() {
+            return Err(Error::NotFound(format!(
+                "Directory not found: {}",
+                directory
+            )));
+        }
+
+        let filter = walk::Filter::new(&options.include, &options.exclude)?;
+        let entries = walk::collect(&source_path, &filter, options.mtime)?;
+        let summary = ArchiveSummary {
+            entries: entries.len(),
+            bytes: entries.iter().map(walk::Entry::size).sum(),
+        };
+
+        let out = BufWriter::new(File::create(archive).map_err(Error::Io)?);
+        let mut out = match format {
+            ArchiveFormat::Tar => tarball::write(&entries, out)?,
+            ArchiveFormat::TarGz => {
+                let level = options
+                    .level
+                    .map_or(flate2::Compression::default(), |level| {
+                        flate2::Compression::new(level.clamp(0, 9) as u32)
+                    });
+                // No name and a zero timestamp in the gzip header
+                let encoder = flate2::GzBuilder::new().mtime(0).write(out, level);
+                tarball::write(&entries, encoder)?
+                    .finish()
+                    .map_err(Error::Io)?
+            }
+            ArchiveFormat::TarZst => {
+                let encoder =
+                    zstd::Encoder::new(out, options.level.unwrap_or(0)).map_err(Error::Io)?;
+                tarball::write(&entries, encoder)?
+                    .finish()
+                    .map_err(Error::Io)?
+            }
+            ArchiveFormat::Cpio => cpio::write(&entries, out)?,
+            ArchiveFormat::Zip => zip::write(&entries, out, options.level)?,
+        };
+        out.flush().map_err(Error::Io)?;
+
+        Ok(summary)
+    }
 }
 
 #[cfg(test)]
@@ -636,4 +785,19 @@ mod tests {
         let mut g = Guestfs::new().unwrap();
         let _ = g;
     }
+
+    #[test]
+    fn test_archive_format() {
+        let format = |name: &str| ArchiveFormat::from_path(Path::new(name));
+        assert_eq!(format("/tmp/etc.tar.zst"), Some(ArchiveFormat::TarZst));
+        assert_eq!(format("backup.TGZ"), Some(ArchiveFormat::TarGz));
+        assert_eq!(format("initrd.cpio"), Some(ArchiveFormat::Cpio));
+        assert_eq!(format("etc.tar.bz2"), None);
+
+        for format in ["tar", "tar.gz", "tar.zst", "cpio", "zip"] {
+            let parsed: ArchiveFormat = format.parse().unwrap();
+            assert_eq!(parsed.to_string(), format);
+        }
+        assert!("rar".parse::<ArchiveFormat>().is_err());
+    }
 }
@@ -0,0 +1,110 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! GNU tar writer
+//!
+//! Headers carry numeric owners and no user or group names, so nothing
+//! about the host ends up in the archive.
+
+use super::walk::{Entry, Kind};
+use crate::core::{Error, Result};
+use std::io::{self, Write};
+
+pub(super) fn write<W: Write>(entries: &[Entry], out: W) -> Result<W> {
+    let mut builder = ::tar::Builder::new(out);
+
+    for entry in entries {
+        let mut header = ::tar::Header::new_gnu();
+        header.set_mode(entry.mode);
+        header.set_uid(entry.uid.into());
+        header.set_gid(entry.gid.into());
+        header.set_mtime(entry.mtime.max(0) as u64);
+        header.set_size(0);
+
+        match &entry.kind {
+            Kind::File { size } => {
+                header.set_entry_type(::tar::EntryType::Regular);
+                header.set_size(*size);
+                builder
+                    .append_data(&mut header, &entry.path, entry.data()?)
+                    .map_err(Error::Io)?;
+                continue;
+            }
+            Kind::Symlink(target) => {
+                header.set_entry_type(::tar::EntryType::Symlink);
+                builder
+                    .append_link(&mut header, &entry.path, target)
+                    .map_err(Error::Io)?;
+                continue;
+            }
+            Kind::Dir => header.set_entry_type(::tar::EntryType::Directory),
+            Kind::CharDevice { major, minor } => {
+                header.set_entry_type(::tar::EntryType::Char);
+                set_device(&mut header, *major, *minor)?;
+            }
+            Kind::BlockDevice { major, minor } => {
+                header.set_entry_type(::tar::EntryType::Block);
+                set_device(&mut header, *major, *minor)?;
+            }
+            Kind::Fifo => header.set_entry_type(::tar::EntryType::Fifo),
+        }
+        builder
+            .append_data(&mut header, &entry.path, io::empty())
+            .map_err(Error::Io)?;
+    }
+
+    builder.into_inner().map_err(Error::Io)
+}
+
+fn set_device(header: &mut ::tar::Header, major: u32, minor: u32) -> Result<()> {
+    header.set_device_major(major).map_err(Error::Io)?;
+    header.set_device_minor(minor).map_err(Error::Io)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::PathBuf;
+
+    #[test]
+    fn test_write() {
+        let tmp = tempfile::tempdir().unwrap();
+        let hostname = tmp.path().join("hostname");
+        std::fs::write(&hostname, "guest\n").unwrap();
+
+        let entry = |path: &str, kind: Kind| Entry {
+            path: PathBuf::from(path),
+            kind,
+            mode: 0o644,
+            uid: 0,
+            gid: 0,
+            mtime: 1_700_000_000,
+            source: hostname.clone(),
+        };
+        let entries = [
+            entry("etc", Kind::Dir),
+            entry("etc/hostname", Kind::File { size: 6 }),
+            entry(
+                "etc/localtime",
+                Kind::Symlink(PathBuf::from("../usr/share/zoneinfo/UTC")),
+            ),
+            entry("dev/console", Kind::CharDevice { major: 5, minor: 1 }),
+        ];
+        let bytes = write(&entries, Vec::new()).unwrap();
+        assert_eq!(bytes, write(&entries, Vec::new()).unwrap());
+
+        let mut archive = ::tar::Archive::new(bytes.as_slice());
+        let mut seen = Vec::new();
+        for file in archive.entries().unwrap() {
+            let mut file = file.unwrap();
+            let header = file.header().clone();
+            let path = file.path().unwrap().into_owned();
+            let mut data = String::new();
+            std::io::Read::read_to_string(&mut file, &mut data).unwrap();
+            seen.push((path, header.entry_type(), data));
+            assert_eq!(header.mtime().unwrap(), 1_700_000_000);
+            assert_eq!(header.username().unwrap(), Some(""));
+        }
+        assert_eq!(seen.len(), 4);
+        assert_eq!(seen[1].2, "guest\n");
+        assert_eq!(seen[3].1, ::tar::EntryType::Char);
+    }
+}
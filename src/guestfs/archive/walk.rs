@@ -0,0 +1,301 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Walk a mounted guest directory into sorted archive entries
+
+use crate::core::{Error, Result};
+use glob::{MatchOptions, Pattern};
+use std::collections::HashSet;
+use std::fs::{self, File};
+use std::io::{self, Read};
+use std::os::unix::fs::{FileTypeExt, MetadataExt};
+use std::path::{Path, PathBuf};
+
+/// `*` stays within a path component
+const MATCH_OPTIONS: MatchOptions = MatchOptions {
+    case_sensitive: true,
+    require_literal_separator: true,
+    require_literal_leading_dot: false,
+};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) enum Kind {
+    File { size: u64 },
+    Dir,
+    Symlink(PathBuf),
+    CharDevice { major: u32, minor: u32 },
+    BlockDevice { major: u32, minor: u32 },
+    Fifo,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub(super) struct Entry {
+    /// Path below the archived directory
+    pub path: PathBuf,
+    pub kind: Kind,
+    /// Permission bits, with setuid, setgid and sticky
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    pub mtime: i64,
+    /// Where the entry is on the host
+    pub source: PathBuf,
+}
+
+impl Entry {
+    pub fn size(&self) -> u64 {
+        match self.kind {
+            Kind::File { size } => size,
+            _ => 0,
+        }
+    }
+
+    /// The file's data, exactly as long as it was when listed
+    pub fn data(&self) -> Result<Data> {
+        let size = self.size();
+        let file = File::open(&self.source).map_err(Error::Io)?;
+        Ok(Data {
+            file: file.take(size),
+            remaining: size,
+        })
+    }
+}
+
+pub(super) struct Data {
+    file: io::Take<File>,
+    remaining: u64,
+}
+
+impl Read for Data {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.file.read(buf)?;
+        if n == 0 && self.remaining > 0 && !buf.is_empty() {
+            return Err(io::Error::new(
+                io::ErrorKind::UnexpectedEof,
+                "file shrank while being archived",
+            ));
+        }
+        self.remaining -= n as u64;
+        Ok(n)
+    }
+}
+
+/// A glob, anchored at the archived directory if it contains `/`
+struct Rule {
+    pattern: Pattern,
+    anchored: bool,
+}
+
+impl Rule {
+    fn new(glob: &str) -> Result<Self> {
+        let trimmed = glob.trim_end_matches('/');
+        let pattern = Pattern::new(trimmed.trim_start_matches('/'))
+            .map_err(|e| Error::InputValidation(format!("Invalid glob '{}': {}", glob, e)))?;
+        Ok(Self {
+            pattern,
+            anchored: trimmed.contains('/'),
+        })
+    }
+
+    fn matches(&self, path: &str) -> bool {
+        if self.anchored {
+            self.pattern.matches_with(path, MATCH_OPTIONS)
+        } else {
+            let name = path.rsplit('/').next().unwrap_or(path);
+            self.pattern.matches_with(name, MATCH_OPTIONS)
+        }
+    }
+}
+
+/// Include and exclude globs
+pub(super) struct Filter {
+    include: Vec<Rule>,
+    exclude: Vec<Rule>,
+}
+
+impl Filter {
+    pub fn new<S: AsRef<str>>(include: &[S], exclude: &[S]) -> Result<Self> {
+        let rules = |globs: &[S]| {
+            globs
+                .iter()
+                .map(|glob| Rule::new(glob.as_ref()))
+                .collect::<Result<Vec<_>>>()
+        };
+        Ok(Self {
+            include: rules(include)?,
+            exclude: rules(exclude)?,
+        })
+    }
+
+    fn excludes(&self, path: &str) -> bool {
+        self.exclude.iter().any(|rule| rule.matches(path))
+    }
+
+    fn includes(&self, path: &str) -> bool {
+        self.include.iter().any(|rule| rule.matches(path))
+    }
+}
+
+/// Entries below `root` that `filter` selects, depth first in byte order
+/// of names, with modification times clamped to `clamp`
+pub(super) fn collect(root: &Path, filter: &Filter, clamp: Option<i64>) -> Result<Vec<Entry>> {
+    let mut entries = Vec::new();
+    walk(root, Path::new(""), filter, clamp, &mut entries)?;
+    if filter.include.is_empty() {
+        return Ok(entries);
+    }
+
+    // Keep what is included, what is below an included directory, and the
+    // directories leading to either
+    let mut included = HashSet::new();
+    let mut needed = HashSet::new();
+    for entry in &entries {
+        let below_included = entry
+            .path
+            .parent()
+            .is_some_and(|parent| included.contains(parent));
+        if below_included || filter.includes(&entry.path.to_string_lossy()) {
+            included.insert(entry.path.clone());
+            needed.extend(entry.path.ancestors().skip(1).map(Path::to_path_buf));
+        }
+    }
+    entries.retain(|entry| included.contains(&entry.path) || needed.contains(&entry.path));
+    Ok(entries)
+}
+
+fn walk(
+    root: &Path,
+    dir: &Path,
+    filter: &Filter,
+    clamp: Option<i64>,
+    out: &mut Vec<Entry>,
+) -> Result<()> {
+    let mut names = fs::read_dir(root.join(dir))
+        .map_err(Error::Io)?
+        .map(|entry| entry.map(|entry| entry.file_name()))
+        .collect::<io::Result<Vec<_>>>()
+        .map_err(Error::Io)?;
+    names.sort();
+
+    for name in names {
+        let path = dir.join(&name);
+        if filter.excludes(&path.to_string_lossy()) {
+            continue;
+        }
+        let source = root.join(&path);
+        let metadata = fs::symlink_metadata(&source).map_err(Error::Io)?;
+        let file_type = metadata.file_type();
+        let kind = if file_type.is_dir() {
+            Kind::Dir
+        } else if file_type.is_file() {
+            Kind::File {
+                size: metadata.len(),
+            }
+        } else if file_type.is_symlink() {
+            Kind::Symlink(fs::read_link(&source).map_err(Error::Io)?)
+        } else if file_type.is_char_device() {
+            let (major, minor) = split_dev(metadata.rdev());
+            Kind::CharDevice { major, minor }
+        } else if file_type.is_block_device() {
+            let (major, minor) = split_dev(metadata.rdev());
+            Kind::BlockDevice { major, minor }
+        } else if file_type.is_fifo() {
+            Kind::Fifo
+        } else {
+            // Sockets only mean something while their server runs
+            continue;
+        };
+
+        let is_dir = kind == Kind::Dir;
+        out.push(Entry {
+            path: path.clone(),
+            kind,
+            mode: metadata.mode() & 0o7777,
+            uid: metadata.uid(),
+            gid: metadata.gid(),
+            mtime: clamp.map_or(metadata.mtime(), |clamp| metadata.mtime().min(clamp)),
+            source,
+        });
+        if is_dir {
+            walk(root, &path, filter, clamp, out)?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Major and minor numbers of a Linux `dev_t`
+fn split_dev(dev: u64) -> (u32, u32) {
+    let major = ((dev >> 8) & 0xfff) | ((dev >> 32) & !0xfff);
+    let minor = (dev & 0xff) | ((dev >> 12) & !0xff);
+    (major as u32, minor as u32)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::fs::symlink;
+
+    fn paths(entries: &[Entry]) -> Vec<&str> {
+        entries
+            .iter()
+            .map(|entry| entry.path.to_str().unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_collect() {
+        let tmp = tempfile::tempdir().unwrap();
+        let root = tmp.path();
+        fs::create_dir_all(root.join("etc/ssh")).unwrap();
+        fs::create_dir_all(root.join("var/log")).unwrap();
+        fs::write(root.join("etc/hostname"), "guest\n").unwrap();
+        fs::write(root.join("etc/ssh/sshd_config"), "").unwrap();
+        fs::write(root.join("var/log/messages"), "boot\n").unwrap();
+        fs::write(root.join("var/log/old.log"), "").unwrap();
+        symlink("../var/log", root.join("etc/logs")).unwrap();
+
+        let none: [&str; 0] = [];
+        let all = collect(root, &Filter::new(&none, &none).unwrap(), None).unwrap();
+        assert_eq!(
+            paths(&all),
+            [
+                "etc",
+                "etc/hostname",
+                "etc/logs",
+                "etc/ssh",
+                "etc/ssh/sshd_config",
+                "var",
+                "var/log",
+                "var/log/messages",
+                "var/log/old.log",
+            ]
+        );
+        assert_eq!(all[1].kind, Kind::File { size: 6 });
+        assert_eq!(all[2].kind, Kind::Symlink(PathBuf::from("../var/log")));
+
+        let filter = Filter::new(&["/etc/ssh", "messages"], &["*.log"]).unwrap();
+        let some = collect(root, &filter, Some(0)).unwrap();
+        assert_eq!(
+            paths(&some),
+            [
+                "etc",
+                "etc/ssh",
+                "etc/ssh/sshd_config",
+                "var",
+                "var/log",
+                "var/log/messages",
+            ]
+        );
+        assert!(some.iter().all(|entry| entry.mtime == 0));
+
+        let filter = Filter::new(&none, &["var/log"]).unwrap();
+        let some = collect(root, &filter, None).unwrap();
+        assert_eq!(paths(&some).last(), Some(&"var"));
+    }
+
+    #[test]
+    fn test_split_dev() {
+        // /dev/console and a minor past 255
+        assert_eq!(split_dev(0x501), (5, 1));
+        assert_eq!(split_dev(0x10_0103), (1, 0x103));
+    }
+}
@@ -0,0 +1,411 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Zip writer
+//!
+//! Files are deflated, everything else is stored. Local headers are
+//! written before the data and patched with its CRC and sizes afterwards,
+//! so no data descriptors are needed. Unix modes go in the external
+//! attributes, and ZIP64 end records take over past 65535 entries or
+//! 4 GiB of archive; single files must stay under 4 GiB.
+
+use super::walk::{Entry, Kind};
+use crate::core::{Error, Result};
+use chrono::{DateTime, Datelike, Timelike};
+use flate2::write::DeflateEncoder;
+use flate2::Compression;
+use flate2::CrcReader;
+use std::io::{self, Seek, SeekFrom, Write};
+use std::os::unix::ffi::OsStrExt;
+
+const LOCAL_HEADER: u32 = 0x0403_4b50;
+const CENTRAL_HEADER: u32 = 0x0201_4b50;
+const END_OF_CENTRAL_DIRECTORY: u32 = 0x0605_4b50;
+const ZIP64_END_OF_CENTRAL_DIRECTORY: u32 = 0x0606_4b50;
+const ZIP64_LOCATOR: u32 = 0x0706_4b50;
+const ZIP64_EXTRA: u16 = 0x0001;
+
+/// Where the CRC sits in a local header
+const LOCAL_CRC_OFFSET: u64 = 14;
+
+/// 2.0 for deflate, 4.5 for ZIP64
+const VERSION_DEFLATE: u16 = 20;
+const VERSION_ZIP64: u16 = 45;
+/// Made on Unix, so readers take the mode from the external attributes
+const MADE_BY_UNIX: u16 = 3 << 8;
+
+const METHOD_STORED: u16 = 0;
+const METHOD_DEFLATED: u16 = 8;
+/// Names are UTF-8
+const FLAG_UTF8: u16 = 0x0800;
+/// MS-DOS directory attribute
+const DOS_DIRECTORY: u32 = 0x10;
+
+const S_IFDIR: u32 = 0o040000;
+const S_IFREG: u32 = 0o100000;
+const S_IFLNK: u32 = 0o120000;
+
+/// What the central directory needs to know about a written entry
+struct Written {
+    name: Vec<u8>,
+    flags: u16,
+    method: u16,
+    time: u16,
+    date: u16,
+    crc: u32,
+    compressed: u32,
+    size: u32,
+    attributes: u32,
+    offset: u64,
+}
+
+pub(super) fn write<W: Write + Seek>(
+    entries: &[Entry],
+    mut out: W,
+    level: Option<i32>,
+) -> Result<W> {
+    let level = level.map_or(Compression::default(), |level| {
+        Compression::new(level.clamp(0, 9) as u32)
+    });
+    let start = out.stream_position().map_err(Error::Io)?;
+
+    let mut written = Vec::with_capacity(entries.len());
+    for entry in entries {
+        let file_type = match entry.kind {
+            Kind::File { .. } => S_IFREG,
+            Kind::Dir => S_IFDIR,
+            Kind::Symlink(_) => S_IFLNK,
+            // Zip has no way to hold device nodes and FIFOs
+            _ => continue,
+        };
+        if entry.size() >= u64::from(u32::MAX) {
+            return Err(Error::Unsupported(format!(
+                "zip cannot hold {}: files must be under 4 GiB",
+                entry.path.display()
+            )));
+        }
+
+        let mut name = entry.path.as_os_str().as_bytes().to_vec();
+        if entry.kind == Kind::Dir {
+            name.push(b'/');
+        }
+        let (time, date) = dos_time(entry.mtime);
+        let flags = match std::str::from_utf8(&name) {
+            Ok(_) => FLAG_UTF8,
+            Err(_) => 0,
+        };
+        let method = match entry.size() {
+            0 => METHOD_STORED,
+            _ => METHOD_DEFLATED,
+        };
+        let dos_attributes = match entry.kind {
+            Kind::Dir => DOS_DIRECTORY,
+            _ => 0,
+        };
+        let mut record = Written {
+            flags,
+            method,
+            time,
+            date,
+            crc: 0,
+            compressed: 0,
+            size: 0,
+            attributes: ((file_type | entry.mode) << 16) | dos_attributes,
+            offset: out.stream_position().map_err(Error::Io)? - start,
+            name,
+        };
+        write_local_header(&mut out, &record)?;
+
+        let (crc, compressed, size) = match &entry.kind {
+            Kind::File { .. } if record.method == METHOD_DEFLATED => {
+                let mut data = CrcReader::new(entry.data()?);
+                let mut counter = Counter::new(&mut out);
+                let mut encoder = DeflateEncoder::new(&mut counter, level);
+                io::copy(&mut data, &mut encoder).map_err(Error::Io)?;
+                encoder.finish().map_err(Error::Io)?;
+                (data.crc().sum(), counter.count, data.crc().amount())
+            }
+            Kind::Symlink(target) => {
+                let mut data = CrcReader::new(target.as_os_str().as_bytes());
+                let mut counter = Counter::new(&mut out);
+                io::copy(&mut data, &mut counter).map_err(Error::Io)?;
+                (data.crc().sum(), counter.count, data.crc().amount())
+            }
+            _ => (0, 0, 0),
+        };
+        let too_big = || {
+            Error::Unsupported(format!(
+                "zip cannot hold {}: it does not compress under 4 GiB",
+                entry.path.display()
+            ))
+        };
+        record.crc = crc;
+        record.compressed = u32::try_from(compressed).map_err(|_| too_big())?;
+        record.size = size;
+
+        let end = out.stream_position().map_err(Error::Io)?;
+        out.seek(SeekFrom::Start(start + record.offset + LOCAL_CRC_OFFSET))
+            .map_err(Error::Io)?;
+        put_u32(&mut out, record.crc)?;
+        put_u32(&mut out, record.compressed)?;
+        put_u32(&mut out, record.size)?;
+        out.seek(SeekFrom::Start(end)).map_err(Error::Io)?;
+
+        written.push(record);
+    }
+
+    let directory_offset = out.stream_position().map_err(Error::Io)? - start;
+    for record in &written {
+        write_central_header(&mut out, record)?;
+    }
+    let directory_size = out.stream_position().map_err(Error::Io)? - start - directory_offset;
+    write_end(
+        &mut out,
+        written.len() as u64,
+        directory_offset,
+        directory_size,
+    )?;
+
+    Ok(out)
+}
+
+fn write_local_header<W: Write>(out: &mut W, record: &Written) -> Result<()> {
+    put_u32(out, LOCAL_HEADER)?;
+    put_u16(out, VERSION_DEFLATE)?;
+    put_u16(out, record.flags)?;
+    put_u16(out, record.method)?;
+    put_u16(out, record.time)?;
+    put_u16(out, record.date)?;
+    // CRC and sizes, patched once the data is written
+    put_u32(out, 0)?;
+    put_u32(out, 0)?;
+    put_u32(out, 0)?;
+    put_u16(out, record.name.len() as u16)?;
+    put_u16(out, 0)?;
+    out.write_all(&record.name).map_err(Error::Io)
+}
+
+fn write_central_header<W: Write>(out: &mut W, record: &Written) -> Result<()> {
+    // Offsets past 4 GiB move to a ZIP64 extra field
+    let zip64 = record.offset >= u64::from(u32::MAX);
+    let (needed, offset, extra_len) = if zip64 {
+        (VERSION_ZIP64, u32::MAX, 12)
+    } else {
+        (VERSION_DEFLATE, record.offset as u32, 0)
+    };
+
+    put_u32(out, CENTRAL_HEADER)?;
+    put_u16(out, MADE_BY_UNIX | VERSION_ZIP64)?;
+    put_u16(out, needed)?;
+    put_u16(out, record.flags)?;
+    put_u16(out, record.method)?;
+    put_u16(out, record.time)?;
+    put_u16(out, record.date)?;
+    put_u32(out, record.crc)?;
+    put_u32(out, record.compressed)?;
+    put_u32(out, record.size)?;
+    put_u16(out, record.name.len() as u16)?;
+    put_u16(out, extra_len)?;
+    // Comment length, disk number and internal attributes
+    put_u16(out, 0)?;
+    put_u16(out, 0)?;
+    put_u16(out, 0)?;
+    put_u32(out, record.attributes)?;
+    put_u32(out, offset)?;
+    out.write_all(&record.name).map_err(Error::Io)?;
+    if zip64 {
+        put_u16(out, ZIP64_EXTRA)?;
+        put_u16(out, 8)?;
+        put_u64(out, record.offset)?;
+    }
+    Ok(())
+}
+
+fn write_end<W: Write>(
+    out: &mut W,
+    entries: u64,
+    directory_offset: u64,
+    directory_size: u64,
+) -> Result<()> {
+    let zip64 = entries >= u64::from(u16::MAX)
+        || directory_offset >= u64::from(u32::MAX)
+        || directory_size >= u64::from(u32::MAX);
+
+    let (count, size, offset) = if zip64 {
+        (u16::MAX, u32::MAX, u32::MAX)
+    } else {
+        (
+            entries as u16,
+            directory_size as u32,
+            directory_offset as u32,
+        )
+    };
+
+    if zip64 {
+        let record_offset = directory_offset + directory_size;
+        put_u32(out, ZIP64_END_OF_CENTRAL_DIRECTORY)?;
+        // Size of the rest of the record
+        put_u64(out, 44)?;
+        put_u16(out, MADE_BY_UNIX | VERSION_ZIP64)?;
+        put_u16(out, VERSION_ZIP64)?;
+        put_u32(out, 0)?;
+        put_u32(out, 0)?;
+        put_u64(out, entries)?;
+        put_u64(out, entries)?;
+        put_u64(out, directory_size)?;
+        put_u64(out, directory_offset)?;
+
+        put_u32(out, ZIP64_LOCATOR)?;
+        put_u32(out, 0)?;
+        put_u64(out, record_offset)?;
+        put_u32(out, 1)?;
+    }
+
+    put_u32(out, END_OF_CENTRAL_DIRECTORY)?;
+    put_u16(out, 0)?;
+    put_u16(out, 0)?;
+    put_u16(out, count)?;
+    put_u16(out, count)?;
+    put_u32(out, size)?;
+    put_u32(out, offset)?;
+    put_u16(out, 0)
+}
+
+/// MS-DOS time and date of a Unix time, read as UTC; DOS dates start in 1980
+fn dos_time(mtime: i64) -> (u16, u16) {
+    let Some(time) = DateTime::from_timestamp(mtime, 0).filter(|time| time.year() >= 1980) else {
+        return (0, (1 << 5) | 1);
+    };
+    let year = (time.year() - 1980).min(127) as u16;
+    (
+        ((time.hour() << 11) | (time.minute() << 5) | (time.second() / 2)) as u16,
+        (year << 9) | ((time.month() << 5) | time.day()) as u16,
+    )
+}
+
+/// Counts what goes through it
+struct Counter<W> {
+    inner: W,
+    count: u64,
+}
+
+impl<W> Counter<W> {
+    fn new(inner: W) -> Self {
+        Self { inner, count: 0 }
+    }
+}
+
+impl<W: Write> Write for Counter<W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.count += n as u64;
+        Ok(n)
+    }
+
+    fn flush(&mut self) -> io::Result<()> {
+        self.inner.flush()
+    }
+}
+
+fn put_u16<W: Write>(out: &mut W, value: u16) -> Result<()> {
+    out.write_all(&value.to_le_bytes()).map_err(Error::Io)
+}
+
+fn put_u32<W: Write>(out: &mut W, value: u32) -> Result<()> {
+    out.write_all(&value.to_le_bytes()).map_err(Error::Io)
+}
+
+fn put_u64<W: Write>(out: &mut W, value: u64) -> Result<()> {
+    out.write_all(&value.to_le_bytes()).map_err(Error::Io)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::{Cursor, Read};
+    use std::path::PathBuf;
+
+    fn le_u16(bytes: &[u8], offset: usize) -> u16 {
+        u16::from_le_bytes([bytes[offset], bytes[offset + 1]])
+    }
+
+    fn le_u32(bytes: &[u8], offset: usize) -> u32 {
+        u32::from_le_bytes(bytes[offset..offset + 4].try_into().unwrap())
+    }
+
+    #[test]
+    fn test_write() {
+        let tmp = tempfile::tempdir().unwrap();
+        let motd = tmp.path().join("motd");
+        std::fs::write(&motd, "hello hello hello hello\n").unwrap();
+
+        let entry = |path: &str, kind: Kind, mode: u32| Entry {
+            path: PathBuf::from(path),
+            kind,
+            mode,
+            uid: 0,
+            gid: 0,
+            mtime: 1_700_000_000,
+            source: motd.clone(),
+        };
+        let entries = [
+            entry("etc", Kind::Dir, 0o755),
+            entry("etc/motd", Kind::File { size: 24 }, 0o644),
+            entry("etc/issue", Kind::Symlink(PathBuf::from("motd")), 0o777),
+            entry("dev/null", Kind::CharDevice { major: 1, minor: 3 }, 0o666),
+        ];
+        let bytes = write(&entries, Cursor::new(Vec::new()), None)
+            .unwrap()
+            .into_inner();
+
+        // The end record points at three central headers
+        let end = bytes.len() - 22;
+        assert_eq!(le_u32(&bytes, end), END_OF_CENTRAL_DIRECTORY);
+        assert_eq!(le_u16(&bytes, end + 10), 3);
+        let mut offset = le_u32(&bytes, end + 16) as usize;
+
+        let mut names = Vec::new();
+        for _ in 0..3 {
+            assert_eq!(le_u32(&bytes, offset), CENTRAL_HEADER);
+            let name_len = le_u16(&bytes, offset + 28) as usize;
+            let name = &bytes[offset + 46..offset + 46 + name_len];
+            names.push(String::from_utf8(name.to_vec()).unwrap());
+
+            // Local header CRC and sizes were patched to match
+            let local = le_u32(&bytes, offset + 42) as usize;
+            assert_eq!(le_u32(&bytes, local), LOCAL_HEADER);
+            assert_eq!(
+                &bytes[local + 14..local + 26],
+                &bytes[offset + 16..offset + 28]
+            );
+
+            let mode = le_u32(&bytes, offset + 38) >> 16;
+            if name == b"etc/motd" {
+                assert_eq!(mode, S_IFREG | 0o644);
+                assert_eq!(le_u16(&bytes, offset + 10), METHOD_DEFLATED);
+                assert_eq!(le_u32(&bytes, offset + 24), 24);
+                let data = local + 30 + name_len;
+                let compressed = le_u32(&bytes, offset + 20) as usize;
+                let mut text = String::new();
+                flate2::read::DeflateDecoder::new(&bytes[data..data + compressed])
+                    .read_to_string(&mut text)
+                    .unwrap();
+                assert_eq!(text, "hello hello hello hello\n");
+            }
+            if name == b"etc/issue" {
+                assert_eq!(mode, S_IFLNK | 0o777);
+                assert_eq!(&bytes[local + 30 + name_len..][..4], b"motd");
+            }
+            offset += 46 + name_len;
+        }
+        assert_eq!(names, ["etc/", "etc/motd", "etc/issue"]);
+    }
+
+    #[test]
+    fn test_dos_time() {
+        // 2023-11-14 22:13:20 UTC
+        assert_eq!(
+            dos_time(1_700_000_000),
+            ((22 << 11) | (13 << 5) | 10, (43 << 9) | (11 << 5) | 14)
+        );
+        assert_eq!(dos_time(0), (0, (1 << 5) | 1));
+    }
+}
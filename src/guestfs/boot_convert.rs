@@ -0,0 +1,360 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Bootloader installation for BIOS/UEFI boot mode conversion
+//!
+//! Once the partition table of an image has been converted (see
+//! [`crate::disk::boot_mode`]), the guest needs a bootloader for its new
+//! firmware:
+//!
+//! - UEFI: the new ESP is mounted at /boot/efi, shim and the signed GRUB
+//!   the guest's packages put there are moved onto it (RHEL family), or
+//!   grub-install writes GRUB to it (Debian family). The removable-media
+//!   path EFI/BOOT is filled too, so firmware without boot entries finds it.
+//!   The ESP goes into /etc/fstab.
+//! - BIOS: grub-install writes GRUB's i386-pc core image to the disk.
+//!
+//! Both regenerate grub.cfg. Commands run in the guest through chroot.
+
+use crate::core::{Error, Result};
+use crate::guestfs::boot_repair::BootRepairReport;
+use crate::guestfs::fstab::FstabEntry;
+use crate::guestfs::Guestfs;
+use std::path::Path;
+
+/// Where a guest mounts its ESP
+const ESP_MOUNTPOINT: &str = "/boot/efi";
+
+/// Where /boot/efi is kept while the ESP is mounted over it
+const ESP_SAVED: &str = "/boot/efi.guestkit";
+
+/// Where grub-install finds the modules of a platform
+const GRUB_MODULE_DIRS: [&str; 3] = ["/usr/lib/grub", "/usr/lib/grub2", "/usr/share/grub2"];
+
+/// GRUB platform and EFI file name suffix of an architecture
+pub fn efi_target(arch: &str) -> Option<(&'static str, &'static str)> {
+    match arch {
+        "x86_64" => Some(("x86_64-efi", "x64")),
+        "aarch64" => Some(("arm64-efi", "aa64")),
+        _ => None,
+    }
+}
+
+/// /etc/fstab with the ESP mounted at /boot/efi by filesystem UUID
+///
+/// An existing /boot/efi entry is repointed, otherwise one is added.
+pub fn fstab_with_esp(content: &str, uuid: &str) -> String {
+    let mut replaced = false;
+    let mut fstab = String::new();
+    for line in content.lines() {
+        match FstabEntry::parse(line) {
+            Some(mut entry) if entry.mountpoint == ESP_MOUNTPOINT => {
+                entry.spec = format!("UUID={}", uuid);
+                entry.fstype = "vfat".to_string();
+                fstab.push_str(&entry.format());
+                replaced = true;
+            }
+            _ => {
+                fstab.push_str(line);
+                fstab.push('\n');
+            }
+        }
+    }
+    if !replaced {
+        fstab.push_str(&format!(
+            "UUID={}\t{}\tvfat\tumask=0077,shortname=winnt\t0\t2\n",
+            uuid, ESP_MOUNTPOINT
+        ));
+    }
+    fstab
+}
+
+/// grub.cfg for the ESP that loads the one regenerated in /boot
+///
+/// `prefix` is the GRUB directory on the filesystem with UUID `uuid`.
+pub fn grub_cfg_stub(uuid: &str, prefix: &str) -> String {
+    format!(
+        "search --no-floppy --fs-uuid --set=dev {}\n\
+         set prefix=($dev){}\n\
+         export $prefix\n\
+         configfile $prefix/grub.cfg\n",
+        uuid, prefix
+    )
+}
+
+impl Guestfs {
+    /// Install a UEFI bootloader on the ESP `esp`
+    ///
+    /// The guest `root` must be mounted read-write; the ESP must be
+    /// formatted FAT and is left mounted at /boot/efi.
+    pub fn install_uefi_boot(&mut self, root: &str, esp: &str) -> Result<BootRepairReport> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: install_uefi_boot {} {}", root, esp);
+        }
+
+        let arch = self.inspect_get_arch(root)?;
+        let (target, suffix) = efi_target(&arch)
+            .ok_or_else(|| Error::Unsupported(format!("UEFI boot of {} guests", arch)))?;
+
+        // Signed shim and GRUB from the guest's packages (shim-x64,
+        // grub2-efi-x64) sit in /boot/efi/EFI/<vendor> even on BIOS guests
+        let signed_vendor = self
+            .ls("/boot/efi/EFI")
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|vendor| vendor != "BOOT")
+            .find(|vendor| {
+                self.is_file(&format!("/boot/efi/EFI/{}/grub{}.efi", vendor, suffix))
+                    .unwrap_or(false)
+            });
+        let grub_install = ["grub-install", "grub2-install"]
+            .into_iter()
+            .find(|program| self.guest_program(program));
+        let has_modules = self.grub_modules(target);
+        if signed_vendor.is_none() && !(grub_install.is_some() && has_modules) {
+            return Err(Error::NotFound(format!(
+                "No UEFI GRUB in the guest; install grub-efi-{} (Debian, Ubuntu) or \
+                 grub2-efi-{} and shim-{} (Fedora, RHEL)",
+                if arch == "x86_64" { "amd64" } else { "arm64" },
+                suffix,
+                suffix
+            )));
+        }
+
+        let mut report = BootRepairReport {
+            bootloaders: vec!["grub2".to_string()],
+            ..Default::default()
+        };
+
+        // Mount the ESP over /boot/efi, keeping what was there
+        let saved = !self.ls(ESP_MOUNTPOINT).unwrap_or_default().is_empty();
+        if saved {
+            self.mv(ESP_MOUNTPOINT, ESP_SAVED)?;
+        }
+        self.mount_rw_options("", esp, ESP_MOUNTPOINT)?;
+        if saved {
+            for entry in self.ls(ESP_SAVED)? {
+                self.cp_r(&format!("{}/{}", ESP_SAVED, entry), ESP_MOUNTPOINT)?;
+            }
+            self.rm_rf(ESP_SAVED)?;
+            report.changes.push(format!(
+                "Moved the contents of {} to the ESP",
+                ESP_MOUNTPOINT
+            ));
+        }
+
+        match signed_vendor {
+            Some(vendor) => self.install_signed_grub(&vendor, suffix, &mut report)?,
+            None => {
+                let program = grub_install.unwrap_or("grub-install");
+                let vendor = self.inspect_get_distro(root)?.to_lowercase();
+                let target = format!("--target={}", target);
+                let bootloader_id = format!("--bootloader-id={}", vendor);
+                let mut arguments = vec![
+                    program,
+                    target.as_str(),
+                    "--efi-directory=/boot/efi",
+                    bootloader_id.as_str(),
+                    "--no-nvram",
+                ];
+                self.command_with_devices(&[], &arguments)?;
+                // EFI/BOOT for firmware without a boot entry for the guest
+                arguments.push("--removable");
+                self.command_with_devices(&[], &arguments)?;
+                report.changes.push(format!(
+                    "Installed GRUB with {} to EFI/{} and EFI/BOOT",
+                    program, vendor
+                ));
+            }
+        }
+
+        let uuid = self.vfs_uuid(esp)?;
+        let fstab = self.cat("/etc/fstab").unwrap_or_default();
+        self.write("/etc/fstab", fstab_with_esp(&fstab, &uuid).as_bytes())?;
+        report.changes.push(format!(
+            "Mounted the ESP (UUID={}) at /boot/efi in /etc/fstab",
+            uuid
+        ));
+
+        self.regenerate_grub_configs(&mut report);
+        Ok(report)
+    }
+
+    /// Install GRUB for BIOS boot to the MBR of the first drive
+    ///
+    /// The guest must be mounted read-write.
+    pub fn install_bios_boot(&mut self, root: &str) -> Result<BootRepairReport> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: install_bios_boot {}", root);
+        }
+
+        let program = ["grub2-install", "grub-install"]
+            .into_iter()
+            .find(|program| self.guest_program(program))
+            .filter(|_| self.grub_modules("i386-pc"))
+            .ok_or_else(|| {
+                Error::NotFound(
+                    "No BIOS GRUB in the guest; install grub-pc (Debian, Ubuntu) or \
+                     grub2-pc (Fedora, RHEL)"
+                        .to_string(),
+                )
+            })?;
+        let device = self
+            .drive_devices()
+            .into_iter()
+            .next()
+            .ok_or_else(|| Error::InvalidState("No drive attached".to_string()))?;
+        let grub_dir = if program == "grub2-install" {
+            "/boot/grub2"
+        } else {
+            "/boot/grub"
+        };
+
+        let mut report = BootRepairReport {
+            bootloaders: vec!["grub2".to_string()],
+            ..Default::default()
+        };
+
+        // UEFI guests of the RHEL family keep grubenv on the ESP, which
+        // BIOS GRUB does not read
+        let grubenv = format!("{}/grubenv", grub_dir);
+        if self.is_symlink(&grubenv).unwrap_or(false) {
+            let link = self.readlink(&grubenv)?;
+            let content = self.cat(&grubenv).unwrap_or_default();
+            self.rm(&grubenv)?;
+            if !content.is_empty() {
+                self.write(&grubenv, content.as_bytes())?;
+            }
+            report.changes.push(format!(
+                "Replaced the {} link to {} with a file",
+                grubenv, link
+            ));
+        }
+
+        let device = device.to_string_lossy();
+        self.command_with_devices(&[], &[program, "--target=i386-pc", &device])?;
+        report
+            .changes
+            .push(format!("Installed GRUB with {} to the MBR", program));
+
+        let config = format!("{}/grub.cfg", grub_dir);
+        match self.grub_mkconfig(&config) {
+            Ok(()) => report.changes.push(format!("Regenerated {}", config)),
+            Err(e) => report
+                .warnings
+                .push(format!("Could not regenerate {}: {}", config, e)),
+        }
+        Ok(report)
+    }
+
+    /// Boot shim or the signed GRUB of `vendor` from the ESP
+    fn install_signed_grub(
+        &mut self,
+        vendor: &str,
+        suffix: &str,
+        report: &mut BootRepairReport,
+    ) -> Result<()> {
+        let vendor_dir = format!("/boot/efi/EFI/{}", vendor);
+        let shim = format!("{}/shim{}.efi", vendor_dir, suffix);
+        let grub = format!("{}/grub{}.efi", vendor_dir, suffix);
+        let loader = if self.is_file(&shim).unwrap_or(false) {
+            shim
+        } else {
+            grub.clone()
+        };
+
+        self.mkdir_p("/boot/efi/EFI/BOOT")?;
+        self.cp(
+            &loader,
+            &format!("/boot/efi/EFI/BOOT/BOOT{}.EFI", suffix.to_uppercase()),
+        )?;
+        self.cp(&grub, &format!("/boot/efi/EFI/BOOT/grub{}.efi", suffix))?;
+        report.changes.push(format!(
+            "Copied {} to EFI/BOOT for firmware without a boot entry",
+            loader
+        ));
+
+        // The signed GRUB reads grub.cfg next to itself
+        let boot = self
+            .mountable_of("/boot")
+            .ok_or_else(|| Error::InvalidState("/boot is not mounted".to_string()))?;
+        let boot_uuid = self.vfs_uuid(&boot)?;
+        let prefix = if self.mountable_of("/") == Some(boot) {
+            "/boot/grub2"
+        } else {
+            "/grub2"
+        };
+        let stub = format!("{}/grub.cfg", vendor_dir);
+        self.write(&stub, grub_cfg_stub(&boot_uuid, prefix).as_bytes())?;
+        report
+            .changes
+            .push(format!("Pointed {} at /boot/grub2/grub.cfg", stub));
+        Ok(())
+    }
+
+    /// Regenerate every grub.cfg the guest boots from
+    fn regenerate_grub_configs(&mut self, report: &mut BootRepairReport) {
+        for config in self.grub_config_paths() {
+            match self.grub_mkconfig(&config) {
+                Ok(()) => report.changes.push(format!("Regenerated {}", config)),
+                Err(e) => report
+                    .warnings
+                    .push(format!("Could not regenerate {}: {}", config, e)),
+            }
+        }
+    }
+
+    /// Whether the guest has GRUB modules for `target` (e.g. "i386-pc")
+    fn grub_modules(&mut self, target: &str) -> bool {
+        GRUB_MODULE_DIRS
+            .iter()
+            .any(|dir| self.is_dir(&format!("{}/{}", dir, target)).unwrap_or(false))
+    }
+
+    /// Mountable of the filesystem holding the guest path `path`
+    fn mountable_of(&self, path: &str) -> Option<String> {
+        let mount_root = self.mount_root.as_ref()?;
+        self.mounted
+            .iter()
+            .filter_map(|(mountable, mountpoint)| {
+                let relative = Path::new(mountpoint).strip_prefix(mount_root).ok()?;
+                let guest = Path::new("/").join(relative);
+                Path::new(path)
+                    .starts_with(&guest)
+                    .then(|| (guest.components().count(), mountable.clone()))
+            })
+            .max()
+            .map(|(_, mountable)| mountable)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fstab_with_esp() {
+        let fstab = "# /etc/fstab\nUUID=1234 / xfs defaults 0 0\n";
+        assert_eq!(
+            fstab_with_esp(fstab, "ABCD-EF01"),
+            "# /etc/fstab\nUUID=1234 / xfs defaults 0 0\n\
+             UUID=ABCD-EF01\t/boot/efi\tvfat\tumask=0077,shortname=winnt\t0\t2\n"
+        );
+
+        let fstab = "UUID=1234 / ext4 defaults 0 1\n/dev/sda15 /boot/efi vfat umask=0077 0 1\n";
+        assert_eq!(
+            fstab_with_esp(fstab, "ABCD-EF01"),
+            "UUID=1234 / ext4 defaults 0 1\n\
+             UUID=ABCD-EF01\t/boot/efi\tvfat\tumask=0077\t0\t1\n"
+        );
+    }
+
+    #[test]
+    fn test_efi_target() {
+        assert_eq!(efi_target("x86_64"), Some(("x86_64-efi", "x64")));
+        assert_eq!(efi_target("aarch64"), Some(("arm64-efi", "aa64")));
+        assert_eq!(efi_target("i686"), None);
+    }
+}
@@ -0,0 +1,565 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Boot repair for converted guests
+//!
+//! A guest moved to another hypervisor boots from other disks, on another
+//! console. Boot repair, on a guest mounted read-write:
+//!
+//! - sets the kernel's console= arguments where the guest keeps them:
+//!   /etc/default/grub, the grubenv `kernelopts` (RHEL 8), Boot Loader
+//!   Specification entries (systemd-boot and grub2 with blscfg) and
+//!   /etc/kernel/cmdline
+//! - adds the virtio drivers to the initramfs (dracut or initramfs-tools)
+//!   and rebuilds it for every installed kernel
+//! - regenerates grub.cfg with the guest's own grub2-mkconfig
+//!
+//! Commands run in the guest through chroot, so they match its bootloader
+//! and kernel versions.
+
+use crate::core::Result;
+use crate::guestfs::Guestfs;
+use serde::Serialize;
+
+/// Drivers a guest needs to boot from virtio disks and talk on virtio
+pub const VIRTIO_MODULES: [&str; 5] = [
+    "virtio_blk",
+    "virtio_scsi",
+    "virtio_pci",
+    "virtio_net",
+    "virtio_console",
+];
+
+/// dracut configuration boot repair adds
+const DRACUT_CONF: &str = "/etc/dracut.conf.d/90-guestkit-virtio.conf";
+
+/// initramfs-tools module list
+const INITRAMFS_TOOLS_MODULES: &str = "/etc/initramfs-tools/modules";
+
+/// Where grub keeps its environment block
+const GRUBENV_PATHS: [&str; 2] = ["/boot/grub2/grubenv", "/boot/grub/grubenv"];
+
+/// Size of a grub environment block, padded with '#'
+const GRUBENV_SIZE: usize = 1024;
+
+/// Directories that may hold Boot Loader Specification entries
+const LOADER_DIRS: [&str; 3] = ["/boot/loader", "/boot/efi/loader", "/efi/loader"];
+
+/// What boot repair changes
+#[derive(Debug, Clone)]
+pub struct BootRepairOptions {
+    /// console= arguments for the kernel (e.g. "tty0", "ttyS0,115200n8");
+    /// empty keeps the guest's
+    pub consoles: Vec<String>,
+    /// Add the virtio drivers to the initramfs and rebuild it
+    pub virtio_initramfs: bool,
+    /// Regenerate grub.cfg
+    pub regenerate_grub: bool,
+}
+
+impl Default for BootRepairOptions {
+    fn default() -> Self {
+        BootRepairOptions {
+            consoles: Vec::new(),
+            virtio_initramfs: true,
+            regenerate_grub: true,
+        }
+    }
+}
+
+/// What boot repair did
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct BootRepairReport {
+    /// Bootloaders found ("grub2", "systemd-boot")
+    pub bootloaders: Vec<String>,
+    /// Files changed and regenerated
+    pub changes: Vec<String>,
+    /// Steps that could not be done
+    pub warnings: Vec<String>,
+}
+
+/// A file holding kernel arguments and how to set its consoles
+struct ConsoleEdit {
+    path: String,
+    /// Edited content, or `None` to leave the file alone
+    edit: fn(&str, &[String]) -> Option<String>,
+}
+
+/// Kernel command line with its console= arguments replaced by `consoles`
+pub fn replace_console_args(cmdline: &str, consoles: &[String]) -> String {
+    let mut args: Vec<String> = cmdline
+        .split_whitespace()
+        .filter(|arg| !arg.starts_with("console="))
+        .map(str::to_string)
+        .collect();
+    args.extend(
+        consoles
+            .iter()
+            .map(|console| format!("console={}", console.trim_start_matches("console="))),
+    );
+    args.join(" ")
+}
+
+/// Value of the shell variable `key` in a file such as /etc/default/grub
+fn shell_var(content: &str, key: &str) -> Option<String> {
+    content
+        .lines()
+        .rev()
+        .find_map(|line| line.trim_start().strip_prefix(key)?.strip_prefix('='))
+        .map(|value| {
+            let value = value.trim();
+            value
+                .strip_prefix('"')
+                .and_then(|value| value.strip_suffix('"'))
+                .or_else(|| {
+                    value
+                        .strip_prefix('\'')
+                        .and_then(|value| value.strip_suffix('\''))
+                })
+                .unwrap_or(value)
+                .to_string()
+        })
+}
+
+/// Set the shell variable `key`, appending it if the file has none
+fn set_shell_var(content: &str, key: &str, value: &str) -> String {
+    let assignment = format!("{}=\"{}\"", key, value);
+    let mut found = false;
+    let mut lines: Vec<String> = content
+        .lines()
+        .map(|line| {
+            let is_key = line
+                .trim_start()
+                .strip_prefix(key)
+                .is_some_and(|rest| rest.starts_with('='));
+            if is_key {
+                found = true;
+                assignment.clone()
+            } else {
+                line.to_string()
+            }
+        })
+        .collect();
+    if !found {
+        lines.push(assignment);
+    }
+    lines.join("\n") + "\n"
+}
+
+/// grub serial command for the first serial console, e.g. ttyS1,38400n8
+fn serial_command(consoles: &[String]) -> Option<String> {
+    consoles.iter().find_map(|console| {
+        let console = console.trim_start_matches("console=");
+        let (device, settings) = console.split_once(',').unwrap_or((console, ""));
+        let unit: u32 = device.strip_prefix("ttyS")?.parse().ok()?;
+        let speed: String = settings.chars().take_while(char::is_ascii_digit).collect();
+        let speed = if speed.is_empty() { "115200" } else { &speed };
+        Some(format!("serial --unit={} --speed={}", unit, speed))
+    })
+}
+
+/// /etc/default/grub with the kernel consoles set
+///
+/// A serial console also gets grub's own menu on it.
+pub fn edit_default_grub(content: &str, consoles: &[String]) -> String {
+    let linux = shell_var(content, "GRUB_CMDLINE_LINUX").unwrap_or_default();
+    let mut content = set_shell_var(
+        content,
+        "GRUB_CMDLINE_LINUX",
+        &replace_console_args(&linux, consoles),
+    );
+    // Only one place may name the consoles
+    if let Some(default) = shell_var(&content, "GRUB_CMDLINE_LINUX_DEFAULT") {
+        content = set_shell_var(
+            &content,
+            "GRUB_CMDLINE_LINUX_DEFAULT",
+            &replace_console_args(&default, &[]),
+        );
+    }
+    if let Some(serial) = serial_command(consoles) {
+        content = set_shell_var(&content, "GRUB_TERMINAL", "console serial");
+        content = set_shell_var(&content, "GRUB_SERIAL_COMMAND", &serial);
+    }
+    content
+}
+
+/// grub environment block with the consoles set in `kernelopts`
+///
+/// None if the block has no `kernelopts` or the result would not fit.
+pub fn edit_grubenv(content: &str, consoles: &[String]) -> Option<String> {
+    let mut found = false;
+    let mut block = String::from("# GRUB Environment Block\n");
+    // The header and the padding are the only lines starting with '#'
+    for line in content
+        .lines()
+        .filter(|line| !line.is_empty() && !line.starts_with('#'))
+    {
+        match line.strip_prefix("kernelopts=") {
+            Some(options) => {
+                found = true;
+                block.push_str(&format!(
+                    "kernelopts={}\n",
+                    replace_console_args(options, consoles)
+                ));
+            }
+            None => {
+                block.push_str(line);
+                block.push('\n');
+            }
+        }
+    }
+    if !found || block.len() > GRUBENV_SIZE {
+        return None;
+    }
+    block.push_str(&"#".repeat(GRUBENV_SIZE - block.len()));
+    Some(block)
+}
+
+/// Boot Loader Specification entry with the consoles set in its options
+///
+/// Options taken from the grubenv (`$kernelopts`) are left to it.
+pub fn edit_loader_entry(content: &str, consoles: &[String]) -> String {
+    let mut found = false;
+    let mut lines = Vec::new();
+    for line in content.lines() {
+        match line.trim_start().split_once(char::is_whitespace) {
+            Some(("options", args)) if !args.contains("$kernelopts") => {
+                found = true;
+                lines.push(format!("options {}", replace_console_args(args, consoles)));
+            }
+            Some(("options", _)) => {
+                found = true;
+                lines.push(line.to_string());
+            }
+            _ => lines.push(line.to_string()),
+        }
+    }
+    if !found {
+        lines.push(format!("options {}", replace_console_args("", consoles)));
+    }
+    lines.join("\n") + "\n"
+}
+
+/// initramfs-tools module list with `modules` added, None if all are there
+pub fn add_initramfs_modules(content: &str, modules: &[&str]) -> Option<String> {
+    let listed: Vec<&str> = content
+        .lines()
+        .map(str::trim)
+        .filter(|line| !line.starts_with('#'))
+        .filter_map(|line| line.split_whitespace().next())
+        .collect();
+    let missing: Vec<&str> = modules
+        .iter()
+        .copied()
+        .filter(|module| !listed.contains(module))
+        .collect();
+    if missing.is_empty() {
+        return None;
+    }
+
+    let mut content = content.to_string();
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str("# Added by guestkit for virtio disks\n");
+    for module in missing {
+        content.push_str(module);
+        content.push('\n');
+    }
+    Some(content)
+}
+
+/// dracut configuration adding `modules` to every initramfs
+fn dracut_conf(modules: &[&str]) -> String {
+    format!(
+        "# Added by guestkit for virtio disks\nadd_drivers+=\" {} \"\n",
+        modules.join(" ")
+    )
+}
+
+impl Guestfs {
+    /// Repair the boot configuration of the guest mounted read-write
+    ///
+    /// See the module documentation for the steps. Steps that fail are
+    /// reported as warnings and the others still run.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use guestkit::guestfs::{BootRepairOptions, Guestfs};
+    ///
+    /// let mut g = Guestfs::new()?;
+    /// g.add_drive("/path/to/converted.qcow2")?;
+    /// g.launch()?;
+    /// let roots = g.inspect_os()?;
+    /// g.mount_os_rw(&roots[0])?;
+    ///
+    /// let options = BootRepairOptions {
+    ///     consoles: vec!["tty0".to_string(), "ttyS0,115200n8".to_string()],
+    ///     ..Default::default()
+    /// };
+    /// let report = g.boot_repair(&options)?;
+    /// for change in &report.changes {
+    ///     println!("{}", change);
+    /// }
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn boot_repair(&mut self, options: &BootRepairOptions) -> Result<BootRepairReport> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: boot_repair {:?}", options);
+        }
+
+        let mut report = BootRepairReport::default();
+        let grub_configs = self.grub_config_paths();
+        let has_default_grub = self.is_file("/etc/default/grub").unwrap_or(false);
+        if !grub_configs.is_empty() || has_default_grub {
+            report.bootloaders.push("grub2".to_string());
+        }
+        let mut loader_entries = Vec::new();
+        for dir in LOADER_DIRS {
+            if self
+                .is_file(&format!("{}/loader.conf", dir))
+                .unwrap_or(false)
+                && !report.bootloaders.iter().any(|b| b == "systemd-boot")
+            {
+                report.bootloaders.push("systemd-boot".to_string());
+            }
+            let entries_dir = format!("{}/entries", dir);
+            for name in self.ls(&entries_dir).unwrap_or_default() {
+                if name.ends_with(".conf") {
+                    loader_entries.push(format!("{}/{}", entries_dir, name));
+                }
+            }
+        }
+
+        if !options.consoles.is_empty() {
+            self.repair_consoles(
+                &options.consoles,
+                has_default_grub,
+                &loader_entries,
+                &mut report,
+            );
+        }
+        if options.virtio_initramfs {
+            self.repair_initramfs(&mut report);
+        }
+        if options.regenerate_grub {
+            for config in &grub_configs {
+                match self.grub_mkconfig(config) {
+                    Ok(()) => report.changes.push(format!("Regenerated {}", config)),
+                    Err(e) => report
+                        .warnings
+                        .push(format!("Could not regenerate {}: {}", config, e)),
+                }
+            }
+        }
+
+        Ok(report)
+    }
+
+    /// Set the console= arguments everywhere the guest keeps kernel arguments
+    fn repair_consoles(
+        &mut self,
+        consoles: &[String],
+        has_default_grub: bool,
+        loader_entries: &[String],
+        report: &mut BootRepairReport,
+    ) {
+        let mut edits = Vec::new();
+        if has_default_grub {
+            edits.push(ConsoleEdit {
+                path: "/etc/default/grub".to_string(),
+                edit: |content, consoles| Some(edit_default_grub(content, consoles)),
+            });
+        }
+        for path in GRUBENV_PATHS {
+            edits.push(ConsoleEdit {
+                path: path.to_string(),
+                edit: edit_grubenv,
+            });
+        }
+        for entry in loader_entries {
+            edits.push(ConsoleEdit {
+                path: entry.clone(),
+                edit: |content, consoles| Some(edit_loader_entry(content, consoles)),
+            });
+        }
+        edits.push(ConsoleEdit {
+            path: "/etc/kernel/cmdline".to_string(),
+            edit: |content, consoles| Some(replace_console_args(content.trim(), consoles) + "\n"),
+        });
+
+        for ConsoleEdit { path, edit } in edits {
+            if !self.is_file(&path).unwrap_or(false) {
+                continue;
+            }
+            let Ok(content) = self.cat(&path) else {
+                report.warnings.push(format!("Could not read {}", path));
+                continue;
+            };
+            let Some(edited) = edit(&content, consoles) else {
+                continue;
+            };
+            if edited == content {
+                continue;
+            }
+            match self.write(&path, edited.as_bytes()) {
+                Ok(()) => report
+                    .changes
+                    .push(format!("Set kernel consoles in {}", path)),
+                Err(e) => report
+                    .warnings
+                    .push(format!("Could not write {}: {}", path, e)),
+            }
+        }
+    }
+
+    /// Add the virtio drivers to the initramfs and rebuild it for all kernels
+    fn repair_initramfs(&mut self, report: &mut BootRepairReport) {
+        let (command, configured): (&[&str], Result<bool>) = if self.guest_program("dracut") {
+            let conf = dracut_conf(&VIRTIO_MODULES);
+            let configured = if self.cat(DRACUT_CONF).is_ok_and(|current| current == conf) {
+                Ok(false)
+            } else {
+                self.mkdir_p("/etc/dracut.conf.d")
+                    .and_then(|()| self.write(DRACUT_CONF, conf.as_bytes()))
+                    .map(|()| true)
+            };
+            (&["dracut", "--force", "--regenerate-all"], configured)
+        } else if self.guest_program("update-initramfs") {
+            let current = self.cat(INITRAMFS_TOOLS_MODULES).unwrap_or_default();
+            let configured = match add_initramfs_modules(&current, &VIRTIO_MODULES) {
+                Some(modules) => self
+                    .write(INITRAMFS_TOOLS_MODULES, modules.as_bytes())
+                    .map(|()| true),
+                None => Ok(false),
+            };
+            (&["update-initramfs", "-u", "-k", "all"], configured)
+        } else {
+            report.warnings.push(
+                "No dracut or initramfs-tools in the guest, initramfs not rebuilt".to_string(),
+            );
+            return;
+        };
+
+        match configured {
+            Ok(true) => report.changes.push(format!(
+                "Added virtio drivers to the {} configuration",
+                command[0]
+            )),
+            Ok(false) => {}
+            Err(e) => {
+                report
+                    .warnings
+                    .push(format!("Could not configure {}: {}", command[0], e));
+                return;
+            }
+        }
+        match self.command_with_devices(&[], command) {
+            Ok(_) => report
+                .changes
+                .push(format!("Rebuilt the initramfs with {}", command[0])),
+            Err(e) => report
+                .warnings
+                .push(format!("Could not rebuild the initramfs: {}", e)),
+        }
+    }
+
+    /// Whether the guest has the program `name` in a system directory
+    pub(crate) fn guest_program(&mut self, name: &str) -> bool {
+        ["/usr/sbin", "/usr/bin", "/sbin", "/bin"]
+            .iter()
+            .any(|dir| self.is_file(&format!("{}/{}", dir, name)).unwrap_or(false))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn consoles(list: &[&str]) -> Vec<String> {
+        list.iter().map(|console| console.to_string()).collect()
+    }
+
+    #[test]
+    fn test_replace_console_args() {
+        assert_eq!(
+            replace_console_args(
+                "ro root=/dev/sda1 console=tty0 quiet console=ttyS1,9600",
+                &consoles(&["tty0", "console=ttyS0,115200n8"])
+            ),
+            "ro root=/dev/sda1 quiet console=tty0 console=ttyS0,115200n8"
+        );
+        assert_eq!(replace_console_args("rhgb console=hvc0", &[]), "rhgb");
+    }
+
+    #[test]
+    fn test_edit_default_grub() {
+        let content = "GRUB_TIMEOUT=5\n\
+                       GRUB_CMDLINE_LINUX='crashkernel=auto console=hvc0'\n\
+                       GRUB_CMDLINE_LINUX_DEFAULT=\"quiet console=tty1\"\n";
+        let edited = edit_default_grub(content, &consoles(&["ttyS1,38400n8"]));
+        assert_eq!(
+            edited,
+            "GRUB_TIMEOUT=5\n\
+             GRUB_CMDLINE_LINUX=\"crashkernel=auto console=ttyS1,38400n8\"\n\
+             GRUB_CMDLINE_LINUX_DEFAULT=\"quiet\"\n\
+             GRUB_TERMINAL=\"console serial\"\n\
+             GRUB_SERIAL_COMMAND=\"serial --unit=1 --speed=38400\"\n"
+        );
+
+        let edited = edit_default_grub("GRUB_TIMEOUT=5", &consoles(&["tty0"]));
+        assert_eq!(
+            edited,
+            "GRUB_TIMEOUT=5\nGRUB_CMDLINE_LINUX=\"console=tty0\"\n"
+        );
+    }
+
+    #[test]
+    fn test_edit_grubenv() {
+        let mut content = String::from(
+            "# GRUB Environment Block\nsaved_entry=abc\nkernelopts=root=/dev/vg/root ro console=hvc0\n",
+        );
+        content.push_str(&"#".repeat(GRUBENV_SIZE - content.len()));
+
+        let edited = edit_grubenv(&content, &consoles(&["ttyS0"])).unwrap();
+        assert_eq!(edited.len(), GRUBENV_SIZE);
+        assert!(edited.starts_with(
+            "# GRUB Environment Block\nsaved_entry=abc\nkernelopts=root=/dev/vg/root ro console=ttyS0\n#"
+        ));
+
+        assert_eq!(
+            edit_grubenv("# GRUB Environment Block\nsaved_entry=abc\n", &[]),
+            None
+        );
+    }
+
+    #[test]
+    fn test_edit_loader_entry() {
+        let entry = "title Fedora\nlinux /vmlinuz-6.1\noptions root=UUID=1 ro console=hvc0\n";
+        assert_eq!(
+            edit_loader_entry(entry, &consoles(&["ttyS0"])),
+            "title Fedora\nlinux /vmlinuz-6.1\noptions root=UUID=1 ro console=ttyS0\n"
+        );
+
+        let entry = "title RHEL\noptions $kernelopts $tuned_params\n";
+        assert_eq!(edit_loader_entry(entry, &consoles(&["ttyS0"])), entry);
+
+        assert_eq!(
+            edit_loader_entry("title Arch\nlinux /vmlinuz-linux", &consoles(&["tty0"])),
+            "title Arch\nlinux /vmlinuz-linux\noptions console=tty0\n"
+        );
+    }
+
+    #[test]
+    fn test_add_initramfs_modules() {
+        let content = "# List of modules\nvirtio_blk\nvirtio_pci foo=1";
+        assert_eq!(
+            add_initramfs_modules(content, &["virtio_blk", "virtio_pci", "virtio_scsi"]).unwrap(),
+            "# List of modules\nvirtio_blk\nvirtio_pci foo=1\n\
+             # Added by guestkit for virtio disks\nvirtio_scsi\n"
+        );
+        assert_eq!(add_initramfs_modules("virtio_blk\n", &["virtio_blk"]), None);
+        assert!(dracut_conf(&VIRTIO_MODULES).contains("add_drivers+=\" virtio_blk virtio_scsi"));
+    }
+}
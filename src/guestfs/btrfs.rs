@@ -2,11 +2,157 @@
 //! Btrfs operations for disk image manipulation
 //!
 //! This implementation provides Btrfs-specific functionality.
+//!
+//! Subvolumes are mounted through mountables of the form
+//! `btrfsvol:DEVICE/SUBVOLUME` (e.g. `btrfsvol:/dev/sda2/@home`), which is
+//! what inspection returns for roots and mountpoints living in subvolumes.
 
 use crate::core::{Error, Result};
 use crate::guestfs::Guestfs;
+use serde::{Deserialize, Serialize};
 use std::process::Command;
 
+/// Prefix of mountables naming a btrfs subvolume
+pub const BTRFSVOL_PREFIX: &str = "btrfsvol:";
+
+/// Id of the top-level subvolume of every btrfs filesystem
+pub const BTRFS_TOP_LEVEL_ID: u64 = 5;
+
+/// Btrfs subvolume
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BtrfsSubvolume {
+    pub id: u64,
+    /// Path relative to the top level of the filesystem
+    pub path: String,
+    /// Id of the subvolume this one is nested in
+    pub top_level: u64,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uuid: Option<String>,
+    /// UUID of the subvolume this is a snapshot of
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub parent_uuid: Option<String>,
+}
+
+impl BtrfsSubvolume {
+    pub fn is_snapshot(&self) -> bool {
+        self.parent_uuid.is_some()
+    }
+}
+
+/// Space used by a subvolume
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BtrfsUsage {
+    /// Bytes referenced by the subvolume
+    pub total: u64,
+    /// Bytes not shared with any other subvolume or snapshot
+    pub exclusive: u64,
+}
+
+/// Mountable for subvolume `path` of `device` (`""` is the top level)
+pub fn btrfsvol_mountable(device: &str, path: &str) -> String {
+    format!(
+        "{}{}/{}",
+        BTRFSVOL_PREFIX,
+        device,
+        path.trim_start_matches('/')
+    )
+}
+
+/// Split a `btrfsvol:` mountable into device and subvolume path
+///
+/// The subvolume path is relative to the top level, `""` for the top level
+/// itself.
+pub fn parse_btrfsvol(mountable: &str) -> Option<(&str, &str)> {
+    split_device_path(mountable.strip_prefix(BTRFSVOL_PREFIX)?)
+}
+
+/// Split `DEVICE/PATH` into the device and the path relative to it
+pub(crate) fn split_device_path(rest: &str) -> Option<(&str, &str)> {
+    let components: Vec<&str> = rest.strip_prefix("/dev/")?.split('/').collect();
+
+    // /dev/sda2, but /dev/mapper/NAME and /dev/VG/LV
+    let device_components = if is_disk_name(components[0]) { 1 } else { 2 };
+    if components.len() < device_components || components[..device_components].contains(&"") {
+        return None;
+    }
+
+    let device_len = "/dev".len()
+        + components[..device_components]
+            .iter()
+            .map(|component| component.len() + 1)
+            .sum::<usize>();
+    let (device, path) = rest.split_at(device_len);
+    Some((device, path.trim_matches('/')))
+}
+
+/// Whether `name` (under /dev) is a disk or partition rather than a
+/// directory of mapped devices or volume group
+fn is_disk_name(name: &str) -> bool {
+    ["sd", "vd", "hd", "xvd", "nvme", "mmcblk"]
+        .iter()
+        .any(|prefix| name.starts_with(prefix))
+}
+
+/// Subvolume path from the `subvol=` option of an fstab entry
+pub fn subvol_option(options: &str) -> Option<&str> {
+    options
+        .split(',')
+        .find_map(|option| option.strip_prefix("subvol="))
+        .map(|path| path.trim_matches('/'))
+}
+
+/// Parse `btrfs subvolume list -q -u` output
+///
+/// Lines look like `ID 257 gen 12 top level 5 parent_uuid - uuid 3c1f... path @home`.
+fn parse_subvolume_list(output: &str) -> Vec<BtrfsSubvolume> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let (fields, path) = line.split_once(" path ")?;
+            let tokens: Vec<&str> = fields.split_whitespace().collect();
+            let value = |key: &str| {
+                tokens
+                    .iter()
+                    .position(|token| *token == key)
+                    .and_then(|i| tokens.get(i + 1))
+                    .copied()
+            };
+            let uuid = |key: &str| value(key).filter(|v| *v != "-").map(str::to_string);
+
+            Some(BtrfsSubvolume {
+                id: value("ID")?.parse().ok()?,
+                path: path.trim_start_matches("<FS_TREE>/").to_string(),
+                top_level: value("level")?.parse().ok()?,
+                uuid: uuid("uuid"),
+                parent_uuid: uuid("parent_uuid"),
+            })
+        })
+        .collect()
+}
+
+/// Parse the summary line of `btrfs filesystem du -s --raw`
+fn parse_du_summary(output: &str) -> Option<BtrfsUsage> {
+    let line = output.lines().rfind(|line| !line.trim().is_empty())?;
+    let mut fields = line.split_whitespace();
+    Some(BtrfsUsage {
+        total: fields.next()?.parse().ok()?,
+        exclusive: fields.next()?.parse().ok()?,
+    })
+}
+
+/// `btrfs` command, through sudo when not running as root
+///
+/// Listing subvolumes and walking extents need CAP_SYS_ADMIN.
+fn btrfs_command() -> Command {
+    if unsafe { libc::geteuid() } != 0 {
+        let mut command = Command::new("sudo");
+        command.arg("btrfs");
+        command
+    } else {
+        Command::new("btrfs")
+    }
+}
+
 impl Guestfs {
     /// Create Btrfs subvolume
     ///
@@ -395,10 +541,148 @@ impl Guestfs {
     }
 }
 
+impl Guestfs {
+    /// List the subvolumes of the btrfs filesystem mounted at `fs`
+    ///
+    /// Paths are relative to the top level whichever subvolume is mounted.
+    pub fn btrfs_subvolumes(&mut self, fs: &str) -> Result<Vec<BtrfsSubvolume>> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: btrfs_subvolumes {}", fs);
+        }
+
+        let host_path = self.resolve_guest_path(fs)?;
+
+        let output = btrfs_command()
+            .args(["subvolume", "list", "-q", "-u"])
+            .arg(&host_path)
+            .output()
+            .map_err(|e| Error::CommandFailed(format!("Failed to execute btrfs: {}", e)))?;
+
+        if !output.status.success() {
+            return Err(Error::CommandFailed(format!(
+                "btrfs subvolume list failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+
+        Ok(parse_subvolume_list(&String::from_utf8_lossy(
+            &output.stdout,
+        )))
+    }
+
+    /// Space used by the subvolume at `path`, and how much of it is
+    /// exclusive (freed if the subvolume is deleted)
+    ///
+    /// Walks every file, so this takes a while on large subvolumes.
+    pub fn btrfs_subvolume_usage(&mut self, path: &str) -> Result<BtrfsUsage> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: btrfs_subvolume_usage {}", path);
+        }
+
+        let host_path = self.resolve_guest_path(path)?;
+
+        let output = btrfs_command()
+            .args(["filesystem", "du", "-s", "--raw"])
+            .arg(&host_path)
+            .output()
+            .map_err(|e| Error::CommandFailed(format!("Failed to execute btrfs: {}", e)))?;
+
+        if !output.status.success() {
+            return Err(Error::CommandFailed(format!(
+                "btrfs filesystem du failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+
+        parse_du_summary(&String::from_utf8_lossy(&output.stdout)).ok_or_else(|| {
+            Error::InvalidFormat("Could not parse btrfs filesystem du output".to_string())
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_parse_btrfsvol() {
+        assert_eq!(
+            parse_btrfsvol("btrfsvol:/dev/sda2/@home"),
+            Some(("/dev/sda2", "@home"))
+        );
+        assert_eq!(
+            parse_btrfsvol("btrfsvol:/dev/sda2/@/.snapshots/1/snapshot"),
+            Some(("/dev/sda2", "@/.snapshots/1/snapshot"))
+        );
+        assert_eq!(
+            parse_btrfsvol("btrfsvol:/dev/sda2/"),
+            Some(("/dev/sda2", ""))
+        );
+        assert_eq!(
+            parse_btrfsvol("btrfsvol:/dev/mapper/luks-1234/@"),
+            Some(("/dev/mapper/luks-1234", "@"))
+        );
+        assert_eq!(
+            parse_btrfsvol("btrfsvol:/dev/vg0/root/@"),
+            Some(("/dev/vg0/root", "@"))
+        );
+        assert_eq!(parse_btrfsvol("/dev/sda2"), None);
+        assert_eq!(parse_btrfsvol("btrfsvol:/dev/mapper"), None);
+
+        let mountable = btrfsvol_mountable("/dev/sda2", "/@home");
+        assert_eq!(mountable, "btrfsvol:/dev/sda2/@home");
+        assert_eq!(parse_btrfsvol(&mountable), Some(("/dev/sda2", "@home")));
+    }
+
+    #[test]
+    fn test_subvol_option() {
+        assert_eq!(
+            subvol_option("defaults,subvol=@home,compress=zstd"),
+            Some("@home")
+        );
+        assert_eq!(subvol_option("subvol=/@/var"), Some("@/var"));
+        assert_eq!(subvol_option("defaults,subvolid=256"), None);
+    }
+
+    #[test]
+    fn test_parse_subvolume_list() {
+        let output = "\
+ID 256 gen 30 top level 5 parent_uuid -                                    uuid 8a3e7c36-9a4e-bb4d-a4a5-0b0c4fd1a2e1 path @
+ID 257 gen 28 top level 5 parent_uuid -                                    uuid 61b5b7a5-0f4c-5e47-9f6b-48d8e0e1c3a2 path @home
+ID 260 gen 25 top level 256 parent_uuid 8a3e7c36-9a4e-bb4d-a4a5-0b0c4fd1a2e1 uuid 0f2d8a91-1e2f-fb49-8c3e-7e1d3c2b1a09 path @/.snapshots/1/snapshot
+ID 261 gen 26 top level 5 parent_uuid -                                    uuid - path my files
+";
+        let subvolumes = parse_subvolume_list(output);
+        assert_eq!(subvolumes.len(), 4);
+        assert_eq!(subvolumes[0].id, 256);
+        assert_eq!(subvolumes[0].path, "@");
+        assert!(!subvolumes[0].is_snapshot());
+        assert_eq!(subvolumes[2].top_level, 256);
+        assert_eq!(subvolumes[2].path, "@/.snapshots/1/snapshot");
+        assert_eq!(subvolumes[2].parent_uuid, subvolumes[0].uuid);
+        assert!(subvolumes[2].is_snapshot());
+        assert_eq!(subvolumes[3].path, "my files");
+        assert_eq!(subvolumes[3].uuid, None);
+    }
+
+    #[test]
+    fn test_parse_du_summary() {
+        let output = "     Total   Exclusive  Set shared  Filename\n\
+                      5368709120   104857600  5263851520  /run/guestctl-1/@\n";
+        assert_eq!(
+            parse_du_summary(output),
+            Some(BtrfsUsage {
+                total: 5_368_709_120,
+                exclusive: 104_857_600,
+            })
+        );
+        assert_eq!(parse_du_summary(""), None);
+    }
+
     #[test]
     fn test_btrfs_api_exists() {
         let mut g = Guestfs::new().unwrap();
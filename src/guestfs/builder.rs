@@ -1,7 +1,9 @@
 // SPDX-License-Identifier: LGPL-3.0-or-later
 //! Builder pattern for creating Guestfs handles with fluent API
 
+use super::bitlocker::BitlockerKey;
 use super::handle::DriveConfig;
+use super::luks::LuksKey;
 use super::Guestfs;
 use crate::core::Result;
 use std::path::Path;
@@ -31,6 +33,8 @@ pub struct GuestfsBuilder {
     autosync: bool,
     selinux: bool,
     identifier: Option<String>,
+    bitlocker_keys: Vec<(Option<String>, BitlockerKey)>,
+    luks_keys: Vec<(Option<String>, LuksKey)>,
 }
 
 impl Default for GuestfsBuilder {
@@ -50,6 +54,8 @@ impl GuestfsBuilder {
             autosync: true,
             selinux: false,
             identifier: None,
+            bitlocker_keys: Vec::new(),
+            luks_keys: Vec::new(),
         }
     }
 
@@ -195,6 +201,57 @@ impl GuestfsBuilder {
         self
     }
 
+    /// Add a key tried on every BitLocker volume at launch
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use guestkit::Guestfs;
+    /// use guestkit::guestfs::BitlockerKey;
+    ///
+    /// let guest = Guestfs::builder()
+    ///     .add_drive_ro("windows.qcow2")
+    ///     .bitlocker_key(BitlockerKey::ClearKey)
+    ///     .build()?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn bitlocker_key(mut self, key: BitlockerKey) -> Self {
+        self.bitlocker_keys.push((None, key));
+        self
+    }
+
+    /// Add a key tried only on the BitLocker volume `device` at launch
+    pub fn bitlocker_key_for<S: Into<String>>(mut self, device: S, key: BitlockerKey) -> Self {
+        self.bitlocker_keys.push((Some(device.into()), key));
+        self
+    }
+
+    /// Add a key tried on every LUKS volume at launch
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// # use guestkit::Guestfs;
+    /// use guestkit::guestfs::LuksKey;
+    ///
+    /// let guest = Guestfs::builder()
+    ///     .add_drive_ro("rhel9.qcow2")
+    ///     .luks_key(LuksKey::Clevis)
+    ///     .build()?;
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn luks_key(mut self, key: LuksKey) -> Self {
+        self.luks_keys.push((None, key));
+        self
+    }
+
+    /// Add a key tried only on the LUKS volume `selector` (device or UUID)
+    /// at launch
+    pub fn luks_key_for<S: Into<String>>(mut self, selector: S, key: LuksKey) -> Self {
+        self.luks_keys.push((Some(selector.into()), key));
+        self
+    }
+
     /// Build the Guestfs handle
     ///
     /// # Errors
@@ -215,6 +272,14 @@ impl GuestfsBuilder {
             guestfs.add_drive_opts(drive.path, drive.readonly, drive.format.as_deref())?;
         }
 
+        for (device, key) in self.bitlocker_keys {
+            guestfs.add_bitlocker_key(device.as_deref(), key)?;
+        }
+
+        for (selector, key) in self.luks_keys {
+            guestfs.add_luks_key(selector.as_deref(), key)?;
+        }
+
         Ok(guestfs)
     }
 
@@ -293,4 +358,32 @@ mod tests {
         assert_eq!(builder.drives.len(), 1);
         assert_eq!(builder.identifier, Some("test-guest".to_string()));
     }
+
+    #[test]
+    fn test_builder_bitlocker_keys() {
+        let guest = Guestfs::builder()
+            .add_drive_ro("/tmp/windows.img")
+            .bitlocker_key(BitlockerKey::ClearKey)
+            .bitlocker_key_for("/dev/sda2", BitlockerKey::Password("secret".to_string()))
+            .build()
+            .unwrap();
+
+        assert_eq!(guest.bitlocker_keys.len(), 2);
+        assert_eq!(guest.bitlocker_keys[0], (None, BitlockerKey::ClearKey));
+        assert_eq!(guest.bitlocker_keys[1].0.as_deref(), Some("/dev/sda2"));
+    }
+
+    #[test]
+    fn test_builder_luks_keys() {
+        let guest = Guestfs::builder()
+            .add_drive_ro("/tmp/encrypted.img")
+            .luks_key(LuksKey::Clevis)
+            .luks_key_for("/dev/sda2", LuksKey::Passphrase("secret".to_string()))
+            .build()
+            .unwrap();
+
+        let keys = &guest.luks_keys[guest.luks_keys.len() - 2..];
+        assert_eq!(keys[0], (None, LuksKey::Clevis));
+        assert_eq!(keys[1].0.as_deref(), Some("/dev/sda2"));
+    }
 }
@@ -8,6 +8,7 @@
 
 use crate::core::{Error, Result};
 use crate::guestfs::Guestfs;
+use std::path::PathBuf;
 use std::process::Command;
 
 impl Guestfs {
@@ -84,6 +85,105 @@ impl Guestfs {
         Ok(String::from_utf8_lossy(&output.stdout).to_string())
     }
 
+    /// Execute a command in the guest with /dev, /proc and /sys available
+    ///
+    /// Tools such as grub2-mkconfig, dracut and update-initramfs look at
+    /// devices and the running kernel. `env` is set for the command only.
+    pub fn command_with_devices(
+        &mut self,
+        env: &[(&str, &str)],
+        arguments: &[&str],
+    ) -> Result<String> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: command_with_devices {:?} {:?}", env, arguments);
+        }
+
+        if arguments.is_empty() {
+            return Err(Error::InvalidFormat("No command provided".to_string()));
+        }
+
+        let root = PathBuf::from(self.find_root_mountpoint()?);
+        let need_sudo = unsafe { libc::geteuid() } != 0;
+        let privileged = |program: &str| {
+            if need_sudo {
+                let mut cmd = Command::new("sudo");
+                cmd.arg(program);
+                cmd
+            } else {
+                Command::new(program)
+            }
+        };
+
+        let mut bound = Vec::new();
+        let mut result = Ok(String::new());
+        for dir in ["dev", "proc", "sys"] {
+            let target = root.join(dir);
+            let output = privileged("mount")
+                .arg("--bind")
+                .arg(format!("/{}", dir))
+                .arg(&target)
+                .output();
+            match output {
+                Ok(output) if output.status.success() => bound.push(target),
+                Ok(output) => {
+                    result = Err(Error::CommandFailed(format!(
+                        "Failed to bind /{} into the guest: {}",
+                        dir,
+                        String::from_utf8_lossy(&output.stderr).trim()
+                    )));
+                    break;
+                }
+                Err(e) => {
+                    result = Err(Error::CommandFailed(format!(
+                        "Failed to execute mount: {}",
+                        e
+                    )));
+                    break;
+                }
+            }
+        }
+
+        if result.is_ok() {
+            // env keeps the variables where sudo would reset them
+            let mut cmd = privileged("env");
+            for (name, value) in env {
+                cmd.arg(format!("{}={}", name, value));
+            }
+            result = cmd
+                .arg("chroot")
+                .arg(&root)
+                .args(arguments)
+                .output()
+                .map_err(|e| Error::CommandFailed(format!("Failed to execute chroot: {}", e)))
+                .and_then(|output| {
+                    if output.status.success() {
+                        Ok(String::from_utf8_lossy(&output.stdout).to_string())
+                    } else {
+                        Err(Error::CommandFailed(format!(
+                            "{} failed with exit code {:?}: {}",
+                            arguments[0],
+                            output.status.code(),
+                            String::from_utf8_lossy(&output.stderr).trim()
+                        )))
+                    }
+                });
+        }
+
+        for target in bound.iter().rev() {
+            let unmounted = privileged("umount")
+                .arg(target)
+                .status()
+                .is_ok_and(|status| status.success());
+            if !unmounted {
+                let _ = privileged("umount").arg("-l").arg(target).status();
+            }
+        }
+
+        result
+    }
+
     /// Execute a command and return output as lines
     ///
     pub fn command_lines(&mut self, arguments: &[&str]) -> Result<Vec<String>> {
@@ -0,0 +1,261 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Container images and containers stored in a guest
+//!
+//! Docker's image store, containers-storage (Podman, Buildah and CRI-O,
+//! system-wide and rootless under each home directory) and containerd's
+//! metadata database are read directly, without running any runtime. The
+//! packages in an image come from the dpkg, apk and pacman databases in its
+//! layers.
+
+mod bolt;
+mod containerd;
+mod docker;
+mod layers;
+mod storage;
+
+use crate::core::Result;
+use crate::guestfs::{Application, Guestfs};
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+
+/// A layer of a container image
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageLayer {
+    /// Digest of the uncompressed layer (its diff ID)
+    pub digest: String,
+    pub size: Option<u64>,
+    /// Unpacked layer directory, or layer tarball, in the guest
+    pub path: Option<String>,
+    /// Media type of a layer tarball, which says how it is compressed
+    pub media_type: Option<String>,
+}
+
+/// A container image
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContainerImage {
+    /// docker, podman (for anything using containers-storage) or containerd
+    pub runtime: String,
+    /// Digest of the image config
+    pub id: String,
+    /// `repository:tag` names
+    pub tags: Vec<String>,
+    /// `repository@digest` manifest references
+    pub digests: Vec<String>,
+    pub created: Option<String>,
+    pub architecture: Option<String>,
+    pub os: Option<String>,
+    /// Base layer first
+    pub layers: Vec<ImageLayer>,
+    /// Where the runtime keeps its images
+    pub storage: String,
+}
+
+impl ContainerImage {
+    /// The first tag, or the short image ID
+    pub fn name(&self) -> String {
+        self.tags.first().cloned().unwrap_or_else(|| {
+            let hex = self.id.trim_start_matches("sha256:");
+            hex[..hex.len().min(12)].to_string()
+        })
+    }
+
+    /// Sum of the layer sizes that are known
+    pub fn size(&self) -> u64 {
+        self.layers.iter().filter_map(|layer| layer.size).sum()
+    }
+}
+
+/// A container
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Container {
+    pub runtime: String,
+    pub id: String,
+    pub name: String,
+    /// Image reference the container was created from
+    pub image: String,
+    pub image_id: Option<String>,
+    pub created: Option<String>,
+    /// Last known state, where the runtime records it
+    pub state: Option<String>,
+    pub storage: String,
+}
+
+/// Images and containers found in a guest
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ContainerInventory {
+    pub images: Vec<ContainerImage>,
+    pub containers: Vec<Container>,
+}
+
+impl ContainerInventory {
+    pub fn is_empty(&self) -> bool {
+        self.images.is_empty() && self.containers.is_empty()
+    }
+
+    /// Containers created from `image`
+    pub fn containers_of<'a>(
+        &'a self,
+        image: &'a ContainerImage,
+    ) -> impl Iterator<Item = &'a Container> + 'a {
+        self.containers.iter().filter(move |container| {
+            container.storage == image.storage
+                && (container.image_id.as_deref() == Some(image.id.as_str())
+                    || image.tags.contains(&container.image))
+        })
+    }
+}
+
+impl Guestfs {
+    /// Find the container images and containers stored in the guest
+    ///
+    /// The guest's filesystems must be mounted.
+    pub fn inspect_containers(&mut self) -> Result<ContainerInventory> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: inspect_containers");
+        }
+
+        let mut inventory = ContainerInventory::default();
+        let docker_root = docker::data_root(self);
+        docker::scan(self, &docker_root, &mut inventory)?;
+        for root in storage::roots(self) {
+            storage::scan(self, &root, &mut inventory)?;
+        }
+        for root in containerd::roots(self) {
+            containerd::scan(self, &root, &mut inventory)?;
+        }
+
+        inventory.images.sort_by(|a, b| {
+            (&a.runtime, &a.storage, a.name()).cmp(&(&b.runtime, &b.storage, b.name()))
+        });
+        inventory
+            .containers
+            .sort_by(|a, b| (&a.runtime, &a.name).cmp(&(&b.runtime, &b.name)));
+        Ok(inventory)
+    }
+
+    /// Packages installed in a container image, read from its layers
+    ///
+    /// Each comes with its package format, as `inspect_get_package_format`
+    /// names it: deb, apk or pacman.
+    pub fn container_image_packages(
+        &mut self,
+        image: &ContainerImage,
+    ) -> Result<Vec<(String, Application)>> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: container_image_packages {}", image.name());
+        }
+
+        layers::packages(self, image)
+    }
+}
+
+/// Parse a JSON file in the guest, if it is there and valid
+fn read_json(g: &mut Guestfs, path: &str) -> Option<Value> {
+    let data = g.read_file(path).ok()?;
+    serde_json::from_slice(&data).ok()
+}
+
+/// Fill in what an OCI or Docker image config says about the image
+fn apply_config(image: &mut ContainerImage, config: &Value) {
+    let text = |key: &str| config[key].as_str().map(str::to_string);
+    image.created = text("created");
+    image.architecture = text("architecture");
+    image.os = text("os");
+    if image.layers.is_empty() {
+        image.layers = config["rootfs"]["diff_ids"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(Value::as_str)
+            .map(|digest| ImageLayer {
+                digest: digest.to_string(),
+                ..Default::default()
+            })
+            .collect();
+    }
+}
+
+/// Split an image name into tag and digest references
+///
+/// `docker.io/library/nginx:1.25` is a tag; with a manifest digest it also
+/// gives `docker.io/library/nginx@sha256:...`.
+fn references(name: &str, digest: Option<&str>) -> (Option<String>, Option<String>) {
+    if let Some((repository, digest)) = name.split_once('@') {
+        return (None, Some(format!("{}@{}", repository, digest)));
+    }
+    // A colon after the last slash starts the tag; one before is a port
+    let repository = match name.rfind(':') {
+        Some(colon) if !name[colon..].contains('/') => &name[..colon],
+        _ => name,
+    };
+    (
+        Some(name.to_string()),
+        digest.map(|digest| format!("{}@{}", repository, digest)),
+    )
+}
+
+/// Add `name` to the tags or digests of `image`, keeping each once
+fn add_reference(image: &mut ContainerImage, name: &str, digest: Option<&str>) {
+    let (tag, digest) = references(name, digest);
+    for (reference, list) in [(tag, &mut image.tags), (digest, &mut image.digests)] {
+        if let Some(reference) = reference {
+            if !list.contains(&reference) {
+                list.push(reference);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_references() {
+        assert_eq!(
+            references("docker.io/library/nginx:1.25", Some("sha256:abc")),
+            (
+                Some("docker.io/library/nginx:1.25".to_string()),
+                Some("docker.io/library/nginx@sha256:abc".to_string())
+            )
+        );
+        assert_eq!(
+            references("registry:5000/app", None),
+            (Some("registry:5000/app".to_string()), None)
+        );
+        assert_eq!(
+            references("nginx@sha256:abc", Some("sha256:def")),
+            (None, Some("nginx@sha256:abc".to_string()))
+        );
+    }
+
+    #[test]
+    fn test_apply_config() {
+        let mut image = ContainerImage::default();
+        apply_config(
+            &mut image,
+            &json!({
+                "architecture": "amd64",
+                "os": "linux",
+                "created": "2024-01-01T00:00:00Z",
+                "rootfs": { "type": "layers", "diff_ids": ["sha256:a", "sha256:b"] }
+            }),
+        );
+        assert_eq!(image.architecture.as_deref(), Some("amd64"));
+        assert_eq!(image.layers.len(), 2);
+        assert_eq!(image.layers[1].digest, "sha256:b");
+
+        image.id = "sha256:0123456789abcdef".to_string();
+        assert_eq!(image.name(), "0123456789ab");
+        add_reference(&mut image, "nginx:1.25", Some("sha256:c"));
+        add_reference(&mut image, "nginx:1.25", Some("sha256:c"));
+        assert_eq!(image.tags, ["nginx:1.25"]);
+        assert_eq!(image.digests, ["nginx@sha256:c"]);
+        assert_eq!(image.name(), "nginx:1.25");
+    }
+}
@@ -0,0 +1,316 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Read-only bbolt reader, enough for containerd's metadata database
+//!
+//! A bbolt file is a B+tree of fixed-size pages. Pages 0 and 1 hold meta
+//! records; the one with the higher transaction id is current and points
+//! at the root bucket. A bucket's value is its own root page, or, for small
+//! buckets, an inline page stored right after the bucket header.
+
+use crate::core::{Error, Result};
+
+const MAGIC: u32 = 0xED0C_DAED;
+const PAGE_HEADER_SIZE: usize = 16;
+const ELEMENT_SIZE: usize = 16;
+const BUCKET_HEADER_SIZE: usize = 16;
+
+const BRANCH_PAGE: u16 = 0x01;
+const LEAF_PAGE: u16 = 0x02;
+const BUCKET_LEAF: u32 = 0x01;
+
+/// Nested buckets deeper than this are taken as a corrupt file
+const MAX_DEPTH: usize = 64;
+
+pub(super) struct Bolt {
+    data: Vec<u8>,
+    page_size: usize,
+    root: u64,
+}
+
+/// What a key in a bucket holds
+pub(super) enum Value<'a> {
+    Bucket(Bucket<'a>),
+    Data(&'a [u8]),
+}
+
+#[derive(Clone, Copy)]
+pub(super) struct Bucket<'a> {
+    db: &'a Bolt,
+    root: u64,
+    /// Inline page of a bucket with no page of its own
+    inline: Option<&'a [u8]>,
+}
+
+impl Bolt {
+    pub fn new(data: Vec<u8>) -> Result<Self> {
+        let first = meta(&data, 0).ok_or_else(|| invalid("no meta page"))?;
+        let second = meta(&data, first.page_size);
+        let current = match second {
+            Some(second) if second.txid > first.txid => second,
+            _ => first,
+        };
+        Ok(Self {
+            data,
+            page_size: current.page_size,
+            root: current.root,
+        })
+    }
+
+    pub fn root(&self) -> Bucket<'_> {
+        Bucket {
+            db: self,
+            root: self.root,
+            inline: None,
+        }
+    }
+
+    fn page(&self, id: u64) -> Result<&[u8]> {
+        let start = usize::try_from(id)
+            .ok()
+            .and_then(|id| id.checked_mul(self.page_size))
+            .filter(|start| start + PAGE_HEADER_SIZE <= self.data.len())
+            .ok_or_else(|| invalid("page out of range"))?;
+        let overflow = u32_at(&self.data, start + 12) as usize;
+        let end = (start + (overflow + 1) * self.page_size).min(self.data.len());
+        Ok(&self.data[start..end])
+    }
+}
+
+impl<'a> Bucket<'a> {
+    /// Keys and values in key order
+    pub fn entries(&self) -> Result<Vec<(&'a [u8], Value<'a>)>> {
+        let mut entries = Vec::new();
+        match self.inline {
+            Some(page) => self.collect(page, 0, &mut entries)?,
+            None => self.collect(self.db.page(self.root)?, 0, &mut entries)?,
+        }
+        Ok(entries)
+    }
+
+    /// The nested bucket `name`
+    pub fn bucket(&self, name: &[u8]) -> Option<Bucket<'a>> {
+        self.entries()
+            .ok()?
+            .into_iter()
+            .find_map(|(key, value)| match value {
+                Value::Bucket(bucket) if key == name => Some(bucket),
+                _ => None,
+            })
+    }
+
+    /// The value of `key`
+    pub fn get(&self, key: &[u8]) -> Option<&'a [u8]> {
+        self.entries()
+            .ok()?
+            .into_iter()
+            .find_map(|(k, value)| match value {
+                Value::Data(data) if k == key => Some(data),
+                _ => None,
+            })
+    }
+
+    /// Nested buckets, by name
+    pub fn buckets(&self) -> Vec<(&'a [u8], Bucket<'a>)> {
+        self.entries()
+            .unwrap_or_default()
+            .into_iter()
+            .filter_map(|(key, value)| match value {
+                Value::Bucket(bucket) => Some((key, bucket)),
+                Value::Data(_) => None,
+            })
+            .collect()
+    }
+
+    fn collect(
+        &self,
+        page: &'a [u8],
+        depth: usize,
+        out: &mut Vec<(&'a [u8], Value<'a>)>,
+    ) -> Result<()> {
+        if depth > MAX_DEPTH || page.len() < PAGE_HEADER_SIZE {
+            return Err(invalid("malformed page"));
+        }
+        let flags = u16::from_le_bytes([page[8], page[9]]);
+        let count = u16::from_le_bytes([page[10], page[11]]) as usize;
+
+        for index in 0..count {
+            let element = PAGE_HEADER_SIZE + index * ELEMENT_SIZE;
+            if element + ELEMENT_SIZE > page.len() {
+                return Err(invalid("element past the end of its page"));
+            }
+            if flags & BRANCH_PAGE != 0 {
+                let child = u64_at(page, element + 8);
+                self.collect(self.db.page(child)?, depth + 1, out)?;
+            } else if flags & LEAF_PAGE != 0 {
+                let element_flags = u32_at(page, element);
+                let pos = element + u32_at(page, element + 4) as usize;
+                let key_size = u32_at(page, element + 8) as usize;
+                let value_size = u32_at(page, element + 12) as usize;
+                let key = page
+                    .get(pos..pos + key_size)
+                    .ok_or_else(|| invalid("key past the end of its page"))?;
+                let value = page
+                    .get(pos + key_size..pos + key_size + value_size)
+                    .ok_or_else(|| invalid("value past the end of its page"))?;
+
+                if element_flags & BUCKET_LEAF == 0 {
+                    out.push((key, Value::Data(value)));
+                    continue;
+                }
+                if value.len() < BUCKET_HEADER_SIZE {
+                    return Err(invalid("short bucket header"));
+                }
+                let root = u64_at(value, 0);
+                let inline = (root == 0).then(|| &value[BUCKET_HEADER_SIZE..]);
+                out.push((
+                    key,
+                    Value::Bucket(Bucket {
+                        db: self.db,
+                        root,
+                        inline,
+                    }),
+                ));
+            } else {
+                return Err(invalid("unexpected page type"));
+            }
+        }
+        Ok(())
+    }
+}
+
+struct Meta {
+    page_size: usize,
+    root: u64,
+    txid: u64,
+}
+
+/// The meta record of the page at `offset`, if it is valid
+fn meta(data: &[u8], offset: usize) -> Option<Meta> {
+    let meta = data.get(offset + PAGE_HEADER_SIZE..offset + PAGE_HEADER_SIZE + 64)?;
+    if u32_at(meta, 0) != MAGIC || u64_at(meta, 56) != fnv1a(&meta[..56]) {
+        return None;
+    }
+    let page_size = u32_at(meta, 8) as usize;
+    if page_size < 512 || !page_size.is_power_of_two() {
+        return None;
+    }
+    Some(Meta {
+        page_size,
+        root: u64_at(meta, 16),
+        txid: u64_at(meta, 48),
+    })
+}
+
+/// 64-bit FNV-1a, the meta checksum
+fn fnv1a(data: &[u8]) -> u64 {
+    data.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ u64::from(byte)).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+fn u32_at(data: &[u8], offset: usize) -> u32 {
+    u32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+fn u64_at(data: &[u8], offset: usize) -> u64 {
+    u64::from_le_bytes(data[offset..offset + 8].try_into().unwrap())
+}
+
+fn invalid(reason: &str) -> Error {
+    Error::InvalidFormat(format!("bbolt database: {}", reason))
+}
+
+#[cfg(test)]
+pub(super) mod tests {
+    use super::*;
+
+    const PAGE_SIZE: usize = 4096;
+
+    /// A key with either the leaf page of an inline bucket or a value
+    pub type Entry<'a> = (&'a [u8], Option<Vec<u8>>, Vec<u8>);
+
+    /// A leaf page holding `entries`
+    pub fn leaf(id: u64, entries: &[Entry]) -> Vec<u8> {
+        let mut page = Vec::new();
+        page.extend_from_slice(&id.to_le_bytes());
+        page.extend_from_slice(&LEAF_PAGE.to_le_bytes());
+        page.extend_from_slice(&(entries.len() as u16).to_le_bytes());
+        page.extend_from_slice(&0u32.to_le_bytes());
+
+        let mut data = Vec::new();
+        let data_start = PAGE_HEADER_SIZE + entries.len() * ELEMENT_SIZE;
+        for (index, (key, inline, value)) in entries.iter().enumerate() {
+            let element = PAGE_HEADER_SIZE + index * ELEMENT_SIZE;
+            let value = match inline {
+                Some(inline) => {
+                    let mut bucket = vec![0; BUCKET_HEADER_SIZE];
+                    bucket.extend_from_slice(inline);
+                    bucket
+                }
+                None => value.clone(),
+            };
+            let flags = if inline.is_some() { BUCKET_LEAF } else { 0 };
+            let pos = data_start + data.len() - element;
+            page.extend_from_slice(&flags.to_le_bytes());
+            page.extend_from_slice(&(pos as u32).to_le_bytes());
+            page.extend_from_slice(&(key.len() as u32).to_le_bytes());
+            page.extend_from_slice(&(value.len() as u32).to_le_bytes());
+            data.extend_from_slice(key);
+            data.extend_from_slice(&value);
+        }
+        page.extend_from_slice(&data);
+        page
+    }
+
+    /// A database whose root bucket is the leaf page `root`
+    pub fn database(root: Vec<u8>) -> Vec<u8> {
+        let mut data = vec![0; 4 * PAGE_SIZE];
+        for (page, txid) in [(0u64, 1u64), (1, 2)] {
+            let offset = page as usize * PAGE_SIZE;
+            let mut meta = Vec::new();
+            meta.extend_from_slice(&MAGIC.to_le_bytes());
+            meta.extend_from_slice(&2u32.to_le_bytes());
+            meta.extend_from_slice(&(PAGE_SIZE as u32).to_le_bytes());
+            meta.extend_from_slice(&0u32.to_le_bytes());
+            meta.extend_from_slice(&3u64.to_le_bytes());
+            meta.extend_from_slice(&0u64.to_le_bytes());
+            meta.extend_from_slice(&2u64.to_le_bytes());
+            meta.extend_from_slice(&4u64.to_le_bytes());
+            meta.extend_from_slice(&txid.to_le_bytes());
+            let checksum = fnv1a(&meta);
+            meta.extend_from_slice(&checksum.to_le_bytes());
+            data[offset..offset + 8].copy_from_slice(&page.to_le_bytes());
+            data[offset + 8..offset + 10].copy_from_slice(&0x04u16.to_le_bytes());
+            data[offset + PAGE_HEADER_SIZE..offset + PAGE_HEADER_SIZE + 64].copy_from_slice(&meta);
+        }
+        data[3 * PAGE_SIZE..3 * PAGE_SIZE + root.len()].copy_from_slice(&root);
+        data
+    }
+
+    #[test]
+    fn test_bolt() {
+        let target = leaf(0, &[(b"digest", None, b"sha256:abc".to_vec())]);
+        let image = leaf(0, &[(b"target", Some(target), Vec::new())]);
+        let images = leaf(
+            0,
+            &[(b"docker.io/library/nginx:1.25", Some(image), Vec::new())],
+        );
+        let root = leaf(
+            3,
+            &[
+                (b"images", Some(images), Vec::new()),
+                (b"version", None, b"\x06".to_vec()),
+            ],
+        );
+        let db = Bolt::new(database(root)).unwrap();
+
+        let root = db.root();
+        assert_eq!(root.get(b"version"), Some(&b"\x06"[..]));
+        let images = root.bucket(b"images").unwrap().buckets();
+        assert_eq!(images.len(), 1);
+        assert_eq!(images[0].0, b"docker.io/library/nginx:1.25");
+        let target = images[0].1.bucket(b"target").unwrap();
+        assert_eq!(target.get(b"digest"), Some(&b"sha256:abc"[..]));
+
+        assert!(Bolt::new(vec![0; PAGE_SIZE]).is_err());
+    }
+}
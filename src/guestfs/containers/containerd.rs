@@ -0,0 +1,337 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! containerd's metadata database and content store
+//!
+//! `io.containerd.metadata.v1.bolt/meta.db` holds, per namespace, the image
+//! names with the digest of their manifest or index, and the containers.
+//! Manifests, configs and layer tarballs are blobs in the content store.
+//! Docker with the containerd image store keeps its images in the `moby`
+//! namespace, Kubernetes in `k8s.io`.
+
+use super::bolt::{Bolt, Bucket};
+use super::{
+    add_reference, apply_config, read_json, Container, ContainerImage, ContainerInventory,
+    ImageLayer,
+};
+use crate::core::Result;
+use crate::guestfs::Guestfs;
+use chrono::{DateTime, SecondsFormat};
+use serde_json::Value;
+use std::collections::HashMap;
+
+const ROOTS: &[&str] = &[
+    "/var/lib/containerd",
+    "/var/lib/rancher/k3s/agent/containerd",
+];
+const METADATA: &str = "io.containerd.metadata.v1.bolt/meta.db";
+const BLOBS: &str = "io.containerd.content.v1.content/blobs";
+
+/// Seconds from 0001-01-01, where Go's time starts, to the Unix epoch
+const GO_EPOCH_OFFSET: i64 = 62_135_596_800;
+
+const INDEX_TYPES: &[&str] = &[
+    "application/vnd.oci.image.index.v1+json",
+    "application/vnd.docker.distribution.manifest.list.v2+json",
+];
+
+pub(super) fn roots(g: &mut Guestfs) -> Vec<String> {
+    ROOTS
+        .iter()
+        .filter(|root| g.exists(&format!("{}/{}", root, METADATA)).unwrap_or(false))
+        .map(|root| root.to_string())
+        .collect()
+}
+
+/// An image name as the metadata database records it
+#[derive(Debug, PartialEq)]
+struct ImageRecord {
+    namespace: String,
+    name: String,
+    digest: String,
+    media_type: String,
+    created: Option<String>,
+}
+
+pub(super) fn scan(g: &mut Guestfs, root: &str, inventory: &mut ContainerInventory) -> Result<()> {
+    let db = Bolt::new(g.read_file(&format!("{}/{}", root, METADATA))?)?;
+    let (records, containers) = read_metadata(&db);
+
+    // Names of one image share its config digest
+    let mut images: Vec<ContainerImage> = Vec::new();
+    let mut by_key: HashMap<(String, String), usize> = HashMap::new();
+    let mut ids: HashMap<(String, String), String> = HashMap::new();
+    for record in records {
+        let Some(mut image) = resolve(g, root, &record.digest, &record.media_type)? else {
+            continue;
+        };
+        let key = (record.namespace.clone(), image.id.clone());
+        ids.insert(
+            (record.namespace.clone(), record.name.clone()),
+            image.id.clone(),
+        );
+        let index = *by_key.entry(key).or_insert_with(|| {
+            image.runtime = "containerd".to_string();
+            image.storage = storage(root, &record.namespace);
+            image.created = image.created.take().or(record.created.clone());
+            images.push(image);
+            images.len() - 1
+        });
+        // Kubernetes also names images by their ID
+        if !record.name.starts_with("sha256:") {
+            add_reference(&mut images[index], &record.name, Some(&record.digest));
+        }
+    }
+    inventory.images.extend(images);
+
+    for (namespace, mut container) in containers {
+        container.image_id = ids
+            .get(&(namespace.clone(), container.image.clone()))
+            .cloned();
+        container.storage = storage(root, &namespace);
+        inventory.containers.push(container);
+    }
+    Ok(())
+}
+
+fn storage(root: &str, namespace: &str) -> String {
+    format!("{} [{}]", root, namespace)
+}
+
+/// Image names and containers in every namespace
+fn read_metadata(db: &Bolt) -> (Vec<ImageRecord>, Vec<(String, Container)>) {
+    let mut images = Vec::new();
+    let mut containers = Vec::new();
+    let Some(version) = db.root().bucket(b"v1") else {
+        return (images, containers);
+    };
+
+    for (namespace, bucket) in version.buckets() {
+        let namespace = String::from_utf8_lossy(namespace).to_string();
+        for (name, image) in bucket
+            .bucket(b"images")
+            .map(|b| b.buckets())
+            .unwrap_or_default()
+        {
+            let Some(target) = image.bucket(b"target") else {
+                continue;
+            };
+            images.push(ImageRecord {
+                namespace: namespace.clone(),
+                name: String::from_utf8_lossy(name).to_string(),
+                digest: text(&target, b"digest").unwrap_or_default(),
+                media_type: text(&target, b"mediatype").unwrap_or_default(),
+                created: image.get(b"createdat").and_then(go_time),
+            });
+        }
+        for (id, record) in bucket
+            .bucket(b"containers")
+            .map(|b| b.buckets())
+            .unwrap_or_default()
+        {
+            let id = String::from_utf8_lossy(id).to_string();
+            let labels = record.bucket(b"labels");
+            let label = |key: &[u8]| labels.as_ref().and_then(|labels| text(labels, key));
+            let name = label(b"nerdctl/name")
+                .or_else(|| label(b"io.kubernetes.container.name"))
+                .unwrap_or_else(|| id.clone());
+            containers.push((
+                namespace.clone(),
+                Container {
+                    runtime: "containerd".to_string(),
+                    id,
+                    name,
+                    image: text(&record, b"image").unwrap_or_default(),
+                    image_id: None,
+                    created: record.get(b"createdat").and_then(go_time),
+                    state: None,
+                    storage: String::new(),
+                },
+            ));
+        }
+    }
+    (images, containers)
+}
+
+fn text(bucket: &Bucket, key: &[u8]) -> Option<String> {
+    bucket
+        .get(key)
+        .map(|value| String::from_utf8_lossy(value).to_string())
+}
+
+/// A Go `time.Time` in its `MarshalBinary` form, as RFC 3339
+fn go_time(data: &[u8]) -> Option<String> {
+    if !matches!(data.first(), Some(1 | 2)) || data.len() < 15 {
+        return None;
+    }
+    let seconds = i64::from_be_bytes(data[1..9].try_into().ok()?);
+    let nanos = u32::from_be_bytes(data[9..13].try_into().ok()?);
+    // Unset timestamps are the zero time
+    if seconds == 0 {
+        return None;
+    }
+    DateTime::from_timestamp(seconds - GO_EPOCH_OFFSET, nanos)
+        .map(|time| time.to_rfc3339_opts(SecondsFormat::AutoSi, true))
+}
+
+fn blob_path(root: &str, digest: &str) -> Option<String> {
+    let (algorithm, hex) = digest.split_once(':')?;
+    if hex.is_empty() || !hex.bytes().all(|b| b.is_ascii_hexdigit()) {
+        return None;
+    }
+    Some(format!("{}/{}/{}/{}", root, BLOBS, algorithm, hex))
+}
+
+/// Follow an index or manifest to the image it describes
+///
+/// An index resolves to the first platform whose manifest was pulled.
+fn resolve(
+    g: &mut Guestfs,
+    root: &str,
+    digest: &str,
+    media_type: &str,
+) -> Result<Option<ContainerImage>> {
+    let Some(mut manifest) = blob_path(root, digest).and_then(|path| read_json(g, &path)) else {
+        return Ok(None);
+    };
+    let is_index = INDEX_TYPES.contains(&media_type) || manifest["manifests"].is_array();
+    if is_index {
+        let entries = manifest["manifests"]
+            .as_array()
+            .cloned()
+            .unwrap_or_default();
+        let pulled = entries.iter().find_map(|entry| {
+            let path = blob_path(root, entry["digest"].as_str()?)?;
+            read_json(g, &path)
+        });
+        match pulled {
+            Some(pulled) => manifest = pulled,
+            None => return Ok(None),
+        }
+    }
+
+    let Some(config_digest) = manifest["config"]["digest"].as_str() else {
+        return Ok(None);
+    };
+    let mut image = ContainerImage {
+        id: config_digest.to_string(),
+        ..Default::default()
+    };
+    let config = blob_path(root, config_digest)
+        .and_then(|path| read_json(g, &path))
+        .unwrap_or_default();
+    apply_config(&mut image, &config);
+
+    let diff_ids = config["rootfs"]["diff_ids"]
+        .as_array()
+        .cloned()
+        .unwrap_or_default();
+    let layers = manifest["layers"].as_array().cloned().unwrap_or_default();
+    image.layers = Vec::with_capacity(layers.len());
+    for (layer, diff_id) in layers.iter().zip(diff_ids.iter().map(Value::as_str)) {
+        let path = match layer["digest"].as_str().and_then(|d| blob_path(root, d)) {
+            Some(path) if g.exists(&path)? => Some(path),
+            _ => None,
+        };
+        image.layers.push(ImageLayer {
+            digest: diff_id.unwrap_or_default().to_string(),
+            size: layer["size"].as_u64(),
+            path,
+            media_type: layer["mediaType"].as_str().map(str::to_string),
+        });
+    }
+    Ok(Some(image))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::bolt::tests::{database, leaf};
+    use super::*;
+
+    fn go_binary(unix: i64, nanos: u32) -> Vec<u8> {
+        let mut data = vec![1];
+        data.extend_from_slice(&(unix + GO_EPOCH_OFFSET).to_be_bytes());
+        data.extend_from_slice(&nanos.to_be_bytes());
+        data.extend_from_slice(&(-1i16).to_be_bytes());
+        data
+    }
+
+    #[test]
+    fn test_go_time() {
+        assert_eq!(
+            go_time(&go_binary(1_714_557_600, 0)).as_deref(),
+            Some("2024-05-01T10:00:00Z")
+        );
+        assert_eq!(go_time(&[1; 4]), None);
+        assert_eq!(go_time(&go_binary(-GO_EPOCH_OFFSET, 0)), None);
+    }
+
+    #[test]
+    fn test_read_metadata() {
+        let target = leaf(
+            0,
+            &[
+                (b"digest", None, b"sha256:abc".to_vec()),
+                (
+                    b"mediatype",
+                    None,
+                    b"application/vnd.oci.image.index.v1+json".to_vec(),
+                ),
+            ],
+        );
+        let image = leaf(
+            0,
+            &[
+                (b"createdat", None, go_binary(1_714_557_600, 0)),
+                (b"target", Some(target), Vec::new()),
+            ],
+        );
+        let images = leaf(
+            0,
+            &[(b"docker.io/library/nginx:1.25", Some(image), Vec::new())],
+        );
+        let labels = leaf(0, &[(b"nerdctl/name", None, b"web".to_vec())]);
+        let container = leaf(
+            0,
+            &[
+                (b"image", None, b"docker.io/library/nginx:1.25".to_vec()),
+                (b"labels", Some(labels), Vec::new()),
+            ],
+        );
+        let containers = leaf(0, &[(b"c0ffee", Some(container), Vec::new())]);
+        let namespace = leaf(
+            0,
+            &[
+                (b"containers", Some(containers), Vec::new()),
+                (b"images", Some(images), Vec::new()),
+            ],
+        );
+        let version = leaf(0, &[(b"default", Some(namespace), Vec::new())]);
+        let root = leaf(3, &[(b"v1", Some(version), Vec::new())]);
+        let db = Bolt::new(database(root)).unwrap();
+
+        let (images, containers) = read_metadata(&db);
+        assert_eq!(
+            images,
+            [ImageRecord {
+                namespace: "default".to_string(),
+                name: "docker.io/library/nginx:1.25".to_string(),
+                digest: "sha256:abc".to_string(),
+                media_type: "application/vnd.oci.image.index.v1+json".to_string(),
+                created: Some("2024-05-01T10:00:00Z".to_string()),
+            }]
+        );
+        assert_eq!(containers.len(), 1);
+        assert_eq!(containers[0].0, "default");
+        assert_eq!(containers[0].1.name, "web");
+        assert_eq!(containers[0].1.image, "docker.io/library/nginx:1.25");
+    }
+
+    #[test]
+    fn test_blob_path() {
+        assert_eq!(
+            blob_path("/var/lib/containerd", "sha256:abc").as_deref(),
+            Some("/var/lib/containerd/io.containerd.content.v1.content/blobs/sha256/abc")
+        );
+        assert_eq!(blob_path("/var/lib/containerd", "sha256:../x"), None);
+        assert_eq!(blob_path("/var/lib/containerd", "abc"), None);
+    }
+}
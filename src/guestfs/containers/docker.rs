@@ -0,0 +1,258 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Docker's image store
+//!
+//! `image/<driver>/repositories.json` names the images whose configs are in
+//! `imagedb/content/sha256`. A layer is found through its chain ID in
+//! `layerdb`, whose `cache-id` is the directory the storage driver unpacked
+//! it to.
+
+use super::{
+    add_reference, apply_config, read_json, Container, ContainerImage, ContainerInventory,
+};
+use crate::core::Result;
+use crate::guestfs::Guestfs;
+use serde_json::Value;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+
+const DAEMON_CONFIG: &str = "/etc/docker/daemon.json";
+const DEFAULT_ROOT: &str = "/var/lib/docker";
+
+/// The daemon's data root
+pub(super) fn data_root(g: &mut Guestfs) -> String {
+    read_json(g, DAEMON_CONFIG)
+        .and_then(|config| {
+            // `graph` is what data-root was called before Docker 17.05
+            config["data-root"]
+                .as_str()
+                .or(config["graph"].as_str())
+                .map(|root| root.trim_end_matches('/').to_string())
+        })
+        .unwrap_or_else(|| DEFAULT_ROOT.to_string())
+}
+
+pub(super) fn scan(g: &mut Guestfs, root: &str, inventory: &mut ContainerInventory) -> Result<()> {
+    let image_dir = format!("{}/image", root);
+    if !g.is_dir(&image_dir)? {
+        return Ok(());
+    }
+
+    for driver in g.ls(&image_dir)? {
+        let base = format!("{}/{}", image_dir, driver);
+        let content = format!("{}/imagedb/content/sha256", base);
+        if !g.is_dir(&content)? {
+            continue;
+        }
+        let names = read_json(g, &format!("{}/repositories.json", base))
+            .map(|repositories| repositories_by_id(&repositories))
+            .unwrap_or_default();
+
+        for hex in g.ls(&content)? {
+            let Some(config) = read_json(g, &format!("{}/{}", content, hex)) else {
+                continue;
+            };
+            let id = format!("sha256:{}", hex);
+            // Untagged images with a parent are build steps, which
+            // `docker images` does not show either
+            let parent = format!("{}/imagedb/metadata/sha256/{}/parent", base, hex);
+            if !names.contains_key(&id) && g.exists(&parent)? {
+                continue;
+            }
+
+            let mut image = ContainerImage {
+                runtime: "docker".to_string(),
+                id: id.clone(),
+                storage: root.to_string(),
+                ..Default::default()
+            };
+            apply_config(&mut image, &config);
+            for name in names.get(&id).into_iter().flatten() {
+                add_reference(&mut image, name, None);
+            }
+            locate_layers(g, root, &base, &driver, &mut image)?;
+            inventory.images.push(image);
+        }
+    }
+
+    scan_containers(g, root, inventory)
+}
+
+/// Image names by image ID, from `repositories.json`
+fn repositories_by_id(repositories: &Value) -> HashMap<String, Vec<String>> {
+    let mut names: HashMap<String, Vec<String>> = HashMap::new();
+    let repositories = repositories["Repositories"]
+        .as_object()
+        .into_iter()
+        .flatten();
+    for references in repositories.filter_map(|(_, references)| references.as_object()) {
+        for (name, id) in references {
+            if let Some(id) = id.as_str() {
+                names.entry(id.to_string()).or_default().push(name.clone());
+            }
+        }
+    }
+    for names in names.values_mut() {
+        names.sort();
+    }
+    names
+}
+
+/// Chain IDs of a layer stack, which name the layers in `layerdb`
+fn chain_ids(diff_ids: &[&str]) -> Vec<String> {
+    let mut chain: Vec<String> = Vec::with_capacity(diff_ids.len());
+    for diff_id in diff_ids {
+        let id = match chain.last() {
+            Some(parent) => format!(
+                "sha256:{:x}",
+                Sha256::digest(format!("{} {}", parent, diff_id))
+            ),
+            None => diff_id.to_string(),
+        };
+        chain.push(id);
+    }
+    chain
+}
+
+/// Fill in where the storage driver keeps each layer, and its size
+fn locate_layers(
+    g: &mut Guestfs,
+    root: &str,
+    base: &str,
+    driver: &str,
+    image: &mut ContainerImage,
+) -> Result<()> {
+    let diff_ids: Vec<&str> = image
+        .layers
+        .iter()
+        .map(|layer| layer.digest.as_str())
+        .collect();
+    let chain = chain_ids(&diff_ids);
+
+    for (layer, chain_id) in image.layers.iter_mut().zip(chain) {
+        let dir = format!(
+            "{}/layerdb/sha256/{}",
+            base,
+            chain_id.trim_start_matches("sha256:")
+        );
+        if !g.is_dir(&dir)? {
+            continue;
+        }
+        layer.size = g
+            .cat(&format!("{}/size", dir))
+            .ok()
+            .and_then(|size| size.trim().parse().ok());
+        let Ok(cache_id) = g.cat(&format!("{}/cache-id", dir)) else {
+            continue;
+        };
+        let path = match driver {
+            "overlay2" | "overlay" => format!("{}/{}/{}/diff", root, driver, cache_id.trim()),
+            "vfs" => format!("{}/vfs/dir/{}", root, cache_id.trim()),
+            "btrfs" => format!("{}/btrfs/subvolumes/{}", root, cache_id.trim()),
+            // zfs and devicemapper layers are not plain directories
+            _ => continue,
+        };
+        if g.is_dir(&path)? {
+            layer.path = Some(path);
+        }
+    }
+    Ok(())
+}
+
+fn scan_containers(g: &mut Guestfs, root: &str, inventory: &mut ContainerInventory) -> Result<()> {
+    let containers = format!("{}/containers", root);
+    if !g.is_dir(&containers)? {
+        return Ok(());
+    }
+    for id in g.ls(&containers)? {
+        if let Some(config) = read_json(g, &format!("{}/{}/config.v2.json", containers, id)) {
+            inventory.containers.push(parse_container(&config, root));
+        }
+    }
+    Ok(())
+}
+
+/// A container from its `config.v2.json`
+fn parse_container(config: &Value, root: &str) -> Container {
+    let text = |value: &Value| value.as_str().map(str::to_string);
+    let state = &config["State"];
+    let state = if state["Running"].as_bool() == Some(true) {
+        if state["Paused"].as_bool() == Some(true) {
+            "paused"
+        } else {
+            "running"
+        }
+    } else if state["Dead"].as_bool() == Some(true) {
+        "dead"
+    } else {
+        "exited"
+    };
+    Container {
+        runtime: "docker".to_string(),
+        id: text(&config["ID"]).unwrap_or_default(),
+        name: config["Name"]
+            .as_str()
+            .unwrap_or_default()
+            .trim_start_matches('/')
+            .to_string(),
+        image: text(&config["Config"]["Image"]).unwrap_or_default(),
+        image_id: text(&config["Image"]),
+        created: text(&config["Created"]),
+        state: Some(state.to_string()),
+        storage: root.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_chain_ids() {
+        let chain = chain_ids(&["sha256:a", "sha256:b"]);
+        assert_eq!(chain[0], "sha256:a");
+        assert_eq!(
+            chain[1],
+            format!("sha256:{:x}", Sha256::digest("sha256:a sha256:b"))
+        );
+        assert!(chain_ids(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_repositories_by_id() {
+        let names = repositories_by_id(&json!({
+            "Repositories": {
+                "nginx": {
+                    "nginx:1.25": "sha256:1",
+                    "nginx@sha256:d": "sha256:1",
+                    "nginx:latest": "sha256:1"
+                },
+                "alpine": { "alpine:3.19": "sha256:2" }
+            }
+        }));
+        assert_eq!(
+            names["sha256:1"],
+            ["nginx:1.25", "nginx:latest", "nginx@sha256:d"]
+        );
+        assert_eq!(names["sha256:2"], ["alpine:3.19"]);
+    }
+
+    #[test]
+    fn test_parse_container() {
+        let container = parse_container(
+            &json!({
+                "ID": "f00d",
+                "Name": "/web",
+                "Image": "sha256:1",
+                "Created": "2024-05-01T10:00:00Z",
+                "Config": { "Image": "nginx:1.25" },
+                "State": { "Running": true, "Paused": false }
+            }),
+            DEFAULT_ROOT,
+        );
+        assert_eq!(container.name, "web");
+        assert_eq!(container.image, "nginx:1.25");
+        assert_eq!(container.image_id.as_deref(), Some("sha256:1"));
+        assert_eq!(container.state.as_deref(), Some("running"));
+    }
+}
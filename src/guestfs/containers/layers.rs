@@ -0,0 +1,323 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Package databases of an image, merged across its layers
+//!
+//! Only the package databases are looked at. Layers are applied base first;
+//! a layer's whiteouts hide what the layers below it hold, in unpacked
+//! overlay layers as character devices 0/0 and opaque directories, and in
+//! layer tarballs as `.wh.<name>` and `.wh..wh..opq` entries.
+
+use super::ContainerImage;
+use crate::core::Result;
+use crate::guestfs::package::{parse_apk_packages, parse_dpkg_status, parse_pacman_desc};
+use crate::guestfs::{Application, Guestfs};
+use flate2::read::GzDecoder;
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader, Read};
+
+const DPKG_STATUS: &str = "var/lib/dpkg/status";
+/// Distroless images keep one status file per package here
+const DPKG_STATUS_D: &str = "var/lib/dpkg/status.d/";
+const APK_INSTALLED: &[&str] = &["lib/apk/db/installed", "usr/lib/apk/db/installed"];
+const PACMAN_LOCAL: &str = "var/lib/pacman/local/";
+
+/// Directories above the databases, where a whiteout would hide them
+const WATCHED_DIRS: &[&str] = &[
+    "var",
+    "var/lib",
+    "var/lib/dpkg",
+    "var/lib/dpkg/status.d",
+    "var/lib/pacman",
+    "var/lib/pacman/local",
+    "lib",
+    "lib/apk",
+    "lib/apk/db",
+    "usr",
+    "usr/lib",
+    "usr/lib/apk",
+    "usr/lib/apk/db",
+];
+
+const WHITEOUT_PREFIX: &str = ".wh.";
+const OPAQUE_WHITEOUT: &str = ".wh..wh..opq";
+const OPAQUE_XATTRS: &[&str] = &["trusted.overlay.opaque", "user.overlay.opaque"];
+
+/// What one layer removes and adds
+#[derive(Debug, Default)]
+struct Changes {
+    /// Hidden paths; an opaque directory hides only what is under it
+    whiteouts: Vec<(String, bool)>,
+    files: Vec<(String, Vec<u8>)>,
+}
+
+/// Package database files of the layers applied so far
+#[derive(Debug, Default)]
+struct Merged {
+    files: BTreeMap<String, Vec<u8>>,
+}
+
+impl Merged {
+    fn apply(&mut self, changes: Changes) {
+        for (path, opaque) in changes.whiteouts {
+            let under = format!("{}/", path);
+            self.files
+                .retain(|file, _| !file.starts_with(&under) && (opaque || *file != path));
+        }
+        self.files.extend(changes.files);
+    }
+
+    /// Installed packages, each with its package format
+    fn packages(&self) -> Vec<(String, Application)> {
+        let mut packages = Vec::new();
+        for (path, content) in &self.files {
+            let content = String::from_utf8_lossy(content);
+            let (format, found) = if path == DPKG_STATUS || path.starts_with(DPKG_STATUS_D) {
+                ("deb", parse_dpkg_status(&content))
+            } else if APK_INSTALLED.contains(&path.as_str()) {
+                ("apk", parse_apk_packages(&content))
+            } else if path.starts_with(PACMAN_LOCAL) {
+                ("pacman", parse_pacman_desc(&content).into_iter().collect())
+            } else {
+                continue;
+            };
+            packages.extend(found.into_iter().map(|app| (format.to_string(), app)));
+        }
+        packages.sort_by(|(_, a), (_, b)| (&a.name, &a.arch).cmp(&(&b.name, &b.arch)));
+        // /lib may be a link to /usr/lib, which gives the apk database twice
+        packages.dedup_by(|(_, a), (_, b)| a.name == b.name && a.arch == b.arch);
+        packages
+    }
+}
+
+/// Whether `path`, relative to the image root, is a package database file
+fn wanted(path: &str) -> bool {
+    path == DPKG_STATUS
+        || APK_INSTALLED.contains(&path)
+        || path.strip_prefix(DPKG_STATUS_D).is_some_and(|name| {
+            !name.is_empty() && !name.contains('/') && !name.ends_with(".md5sums")
+        })
+        || path
+            .strip_prefix(PACMAN_LOCAL)
+            .and_then(|entry| entry.strip_suffix("/desc"))
+            .is_some_and(|entry| !entry.is_empty() && !entry.contains('/'))
+}
+
+pub(super) fn packages(
+    g: &mut Guestfs,
+    image: &ContainerImage,
+) -> Result<Vec<(String, Application)>> {
+    let mut merged = Merged::default();
+    for layer in &image.layers {
+        let Some(path) = &layer.path else {
+            continue;
+        };
+        let changes = if layer.media_type.is_some() {
+            tar_changes(File::open(g.resolve_guest_path(path)?)?)?
+        } else {
+            dir_changes(g, path)?
+        };
+        merged.apply(changes);
+    }
+    Ok(merged.packages())
+}
+
+/// Changes in a layer tarball, compressed with gzip or zstd or not at all
+fn tar_changes<R: Read>(blob: R) -> io::Result<Changes> {
+    let mut blob = BufReader::new(blob);
+    let magic = blob.fill_buf()?;
+    let reader: Box<dyn Read> = if magic.starts_with(&[0x1f, 0x8b]) {
+        Box::new(GzDecoder::new(blob))
+    } else if magic.starts_with(&[0x28, 0xb5, 0x2f, 0xfd]) {
+        Box::new(zstd::Decoder::with_buffer(blob)?)
+    } else {
+        Box::new(blob)
+    };
+
+    let mut changes = Changes::default();
+    let mut archive = tar::Archive::new(reader);
+    for entry in archive.entries()? {
+        let mut entry = entry?;
+        let path = entry.path()?.to_string_lossy().to_string();
+        let path = path.trim_start_matches("./").trim_matches('/');
+        let (dir, name) = match path.rsplit_once('/') {
+            Some((dir, name)) => (dir, name),
+            None => ("", path),
+        };
+
+        if name == OPAQUE_WHITEOUT {
+            changes.whiteouts.push((dir.to_string(), true));
+        } else if let Some(hidden) = name.strip_prefix(WHITEOUT_PREFIX) {
+            let hidden = match dir {
+                "" => hidden.to_string(),
+                dir => format!("{}/{}", dir, hidden),
+            };
+            changes.whiteouts.push((hidden, false));
+        } else if entry.header().entry_type().is_file() && wanted(path) {
+            let path = path.to_string();
+            let mut content = Vec::new();
+            entry.read_to_end(&mut content)?;
+            changes.files.push((path, content));
+        }
+    }
+    Ok(changes)
+}
+
+/// What is at `path` in an unpacked overlay layer
+enum Entry {
+    Whiteout,
+    Dir,
+    File,
+    Other,
+}
+
+fn entry(g: &mut Guestfs, path: &str) -> Option<Entry> {
+    let stat = g.lstat(path).ok()?;
+    Some(match stat.mode & libc::S_IFMT {
+        libc::S_IFCHR if stat.rdev == 0 => Entry::Whiteout,
+        libc::S_IFDIR => Entry::Dir,
+        libc::S_IFREG => Entry::File,
+        _ => Entry::Other,
+    })
+}
+
+/// Changes in a layer unpacked to `dir`, as overlay storage drivers do
+fn dir_changes(g: &mut Guestfs, dir: &str) -> Result<Changes> {
+    let mut changes = Changes::default();
+    let mut candidates: Vec<String> = APK_INSTALLED.iter().map(|p| p.to_string()).collect();
+    candidates.push(DPKG_STATUS.to_string());
+
+    for watched in WATCHED_DIRS {
+        let path = format!("{}/{}", dir, watched);
+        match entry(g, &path) {
+            Some(Entry::Whiteout) => changes.whiteouts.push((watched.to_string(), false)),
+            Some(Entry::Dir) => {
+                let opaque = OPAQUE_XATTRS.iter().any(|xattr| {
+                    g.getxattr(&path, xattr)
+                        .is_ok_and(|value| value.starts_with(b"y"))
+                });
+                if opaque {
+                    changes.whiteouts.push((watched.to_string(), true));
+                }
+            }
+            _ => {}
+        }
+    }
+
+    for listing in [DPKG_STATUS_D, PACMAN_LOCAL] {
+        let path = format!("{}/{}", dir, listing.trim_end_matches('/'));
+        if !matches!(entry(g, &path), Some(Entry::Dir)) {
+            continue;
+        }
+        for name in g.ls(&path)? {
+            let relative = format!("{}{}", listing, name);
+            if matches!(
+                entry(g, &format!("{}/{}", dir, relative)),
+                Some(Entry::Whiteout)
+            ) {
+                changes.whiteouts.push((relative, false));
+            } else if listing == PACMAN_LOCAL {
+                candidates.push(format!("{}/desc", relative));
+            } else {
+                candidates.push(relative);
+            }
+        }
+    }
+
+    for relative in candidates.into_iter().filter(|path| wanted(path)) {
+        let path = format!("{}/{}", dir, relative);
+        match entry(g, &path) {
+            Some(Entry::Whiteout) => changes.whiteouts.push((relative, false)),
+            Some(Entry::File) => {
+                let content = g.read_file(&path)?;
+                changes.files.push((relative, content));
+            }
+            _ => {}
+        }
+    }
+    Ok(changes)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use flate2::write::GzEncoder;
+    use flate2::Compression;
+
+    fn layer(entries: &[(&str, &str)]) -> Vec<u8> {
+        let mut builder = tar::Builder::new(GzEncoder::new(Vec::new(), Compression::fast()));
+        for (path, content) in entries {
+            let mut header = tar::Header::new_gnu();
+            header.set_size(content.len() as u64);
+            header.set_mode(0o644);
+            header.set_cksum();
+            builder
+                .append_data(&mut header, path, content.as_bytes())
+                .unwrap();
+        }
+        builder.into_inner().unwrap().finish().unwrap()
+    }
+
+    #[test]
+    fn test_wanted() {
+        assert!(wanted("var/lib/dpkg/status"));
+        assert!(wanted("var/lib/dpkg/status.d/libc6"));
+        assert!(!wanted("var/lib/dpkg/status.d/libc6.md5sums"));
+        assert!(wanted("lib/apk/db/installed"));
+        assert!(wanted("var/lib/pacman/local/bash-5.2.026-2/desc"));
+        assert!(!wanted("var/lib/pacman/local/bash-5.2.026-2/files"));
+        assert!(!wanted("etc/passwd"));
+    }
+
+    #[test]
+    fn test_merge_layers() {
+        let base = layer(&[
+            (
+                "./var/lib/dpkg/status",
+                "Package: libc6\nStatus: install ok installed\nVersion: 2.36-9\n\n\
+                 Package: curl\nStatus: install ok installed\nVersion: 7.88.1-10\n",
+            ),
+            ("etc/hostname", "base\n"),
+        ]);
+        let upper = layer(&[
+            (
+                "var/lib/dpkg/status",
+                "Package: libc6\nStatus: install ok installed\nVersion: 2.36-9+deb12u4\n",
+            ),
+            ("var/lib/dpkg/status.d/.wh.old", ""),
+        ]);
+
+        let mut merged = Merged::default();
+        merged.apply(tar_changes(base.as_slice()).unwrap());
+        assert_eq!(merged.files.len(), 1);
+        merged.apply(tar_changes(upper.as_slice()).unwrap());
+        let packages = merged.packages();
+        assert_eq!(packages.len(), 1);
+        assert_eq!(packages[0].0, "deb");
+        assert_eq!(packages[0].1.release, "9+deb12u4");
+
+        // An opaque directory hides what is under it, not itself
+        let apk = layer(&[("lib/apk/db/installed", "P:musl\nV:1.2.4-r2\n")]);
+        let opaque = layer(&[("var/lib/dpkg/.wh..wh..opq", "")]);
+        merged.apply(tar_changes(apk.as_slice()).unwrap());
+        merged.apply(tar_changes(opaque.as_slice()).unwrap());
+        assert_eq!(merged.packages()[0].0, "apk");
+        assert_eq!(merged.packages()[0].1.name, "musl");
+
+        let removed = layer(&[("lib/.wh.apk", "")]);
+        merged.apply(tar_changes(removed.as_slice()).unwrap());
+        assert!(merged.packages().is_empty());
+    }
+
+    #[test]
+    fn test_tar_changes_uncompressed() {
+        let mut builder = tar::Builder::new(Vec::new());
+        let mut header = tar::Header::new_gnu();
+        header.set_size(0);
+        header.set_cksum();
+        builder
+            .append_data(&mut header, ".wh.var", &[][..])
+            .unwrap();
+        let changes = tar_changes(builder.into_inner().unwrap().as_slice()).unwrap();
+        assert_eq!(changes.whiteouts, [("var".to_string(), false)]);
+    }
+}
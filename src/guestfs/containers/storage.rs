@@ -0,0 +1,241 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! containers-storage, shared by Podman, Buildah and CRI-O
+//!
+//! Each storage driver keeps `<driver>-images/images.json`,
+//! `<driver>-layers/layers.json` and `<driver>-containers/containers.json`.
+//! An image names its top layer, and each layer its parent.
+
+use super::{
+    add_reference, apply_config, read_json, Container, ContainerImage, ContainerInventory,
+    ImageLayer,
+};
+use crate::core::Result;
+use crate::guestfs::Guestfs;
+use base64::Engine;
+use serde_json::Value;
+use std::collections::HashMap;
+
+const STORAGE_CONFIG: &str = "/etc/containers/storage.conf";
+const DEFAULT_ROOT: &str = "/var/lib/containers/storage";
+/// Rootless storage, under a home directory
+const USER_ROOT: &str = ".local/share/containers/storage";
+
+const DRIVERS: &[&str] = &["overlay", "vfs", "btrfs"];
+
+/// System-wide storage, then each user's rootless storage
+pub(super) fn roots(g: &mut Guestfs) -> Vec<String> {
+    let system = g
+        .cat(STORAGE_CONFIG)
+        .ok()
+        .and_then(|config| graph_root(&config))
+        .unwrap_or_else(|| DEFAULT_ROOT.to_string());
+    let mut roots = vec![system, format!("/root/{}", USER_ROOT)];
+    if let Ok(users) = g.ls("/home") {
+        roots.extend(
+            users
+                .iter()
+                .map(|user| format!("/home/{}/{}", user, USER_ROOT)),
+        );
+    }
+    roots
+}
+
+/// `graphroot` from `storage.conf`
+fn graph_root(config: &str) -> Option<String> {
+    let config: toml::Table = toml::from_str(config).ok()?;
+    config
+        .get("storage")?
+        .get("graphroot")?
+        .as_str()
+        .map(|root| root.trim_end_matches('/').to_string())
+}
+
+pub(super) fn scan(g: &mut Guestfs, root: &str, inventory: &mut ContainerInventory) -> Result<()> {
+    if !g.is_dir(root)? {
+        return Ok(());
+    }
+
+    for driver in DRIVERS {
+        let images = format!("{}/{}-images", root, driver);
+        let Some(records) = read_json(g, &format!("{}/images.json", images)) else {
+            continue;
+        };
+        let layers =
+            read_json(g, &format!("{}/{}-layers/layers.json", root, driver)).unwrap_or_default();
+
+        for mut image in parse_images(&records, &layers, root, driver) {
+            let hex = image.id.trim_start_matches("sha256:");
+            let config = format!(
+                "{}/{}/={}",
+                images,
+                hex,
+                base64::engine::general_purpose::STANDARD.encode(&image.id)
+            );
+            if let Some(config) = read_json(g, &config) {
+                apply_config(&mut image, &config);
+            }
+            for layer in &mut image.layers {
+                if let Some(path) = layer.path.take() {
+                    layer.path = g.is_dir(&path)?.then_some(path);
+                }
+            }
+            inventory.images.push(image);
+        }
+
+        if let Some(records) = read_json(
+            g,
+            &format!("{}/{}-containers/containers.json", root, driver),
+        ) {
+            inventory
+                .containers
+                .extend(parse_containers(&records, root));
+        }
+    }
+    Ok(())
+}
+
+/// Images from `images.json`, with their layers from `layers.json`
+fn parse_images(images: &Value, layers: &Value, root: &str, driver: &str) -> Vec<ContainerImage> {
+    let layers: HashMap<&str, &Value> = layers
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|layer| Some((layer["id"].as_str()?, layer)))
+        .collect();
+
+    let mut found = Vec::new();
+    for record in images.as_array().into_iter().flatten() {
+        let Some(id) = record["id"].as_str() else {
+            continue;
+        };
+        let mut image = ContainerImage {
+            runtime: "podman".to_string(),
+            id: format!("sha256:{}", id),
+            created: record["created"].as_str().map(str::to_string),
+            storage: root.to_string(),
+            ..Default::default()
+        };
+        let digest = record["digest"].as_str();
+        for name in record["names"].as_array().into_iter().flatten() {
+            if let Some(name) = name.as_str() {
+                add_reference(&mut image, name, digest);
+            }
+        }
+
+        // Walk from the top layer down to the base
+        let mut next = record["layer"].as_str();
+        while let Some(layer) = next.and_then(|id| layers.get(id)) {
+            let id = layer["id"].as_str().unwrap_or_default();
+            image.layers.push(ImageLayer {
+                digest: layer["diff-digest"]
+                    .as_str()
+                    .unwrap_or_default()
+                    .to_string(),
+                size: layer["diff-size"].as_u64(),
+                path: layer_path(root, driver, id),
+                media_type: None,
+            });
+            next = layer["parent"].as_str();
+            if image.layers.len() > layers.len() {
+                // A parent loop
+                break;
+            }
+        }
+        image.layers.reverse();
+        found.push(image);
+    }
+    found
+}
+
+/// Where `driver` unpacks layer `id`
+fn layer_path(root: &str, driver: &str, id: &str) -> Option<String> {
+    match driver {
+        "overlay" => Some(format!("{}/overlay/{}/diff", root, id)),
+        "vfs" => Some(format!("{}/vfs/dir/{}", root, id)),
+        "btrfs" => Some(format!("{}/btrfs/subvolumes/{}", root, id)),
+        _ => None,
+    }
+}
+
+/// Containers from `containers.json`
+fn parse_containers(containers: &Value, root: &str) -> Vec<Container> {
+    let text = |value: &Value| value.as_str().map(str::to_string);
+    containers
+        .as_array()
+        .into_iter()
+        .flatten()
+        .filter_map(|record| {
+            // Podman keeps the image name in a JSON string of its own
+            let metadata: Value = record["metadata"]
+                .as_str()
+                .and_then(|metadata| serde_json::from_str(metadata).ok())
+                .unwrap_or_default();
+            Some(Container {
+                runtime: "podman".to_string(),
+                id: text(&record["id"])?,
+                name: record["names"][0].as_str().unwrap_or_default().to_string(),
+                image: text(&metadata["image-name"]).unwrap_or_default(),
+                image_id: record["image"].as_str().map(|id| format!("sha256:{}", id)),
+                created: text(&record["created"]),
+                state: None,
+                storage: root.to_string(),
+            })
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn test_graph_root() {
+        let config = "[storage]\ndriver = \"overlay\"\ngraphroot = \"/srv/containers/\"\n";
+        assert_eq!(graph_root(config).as_deref(), Some("/srv/containers"));
+        assert_eq!(graph_root("[storage]\ndriver = \"vfs\"\n"), None);
+    }
+
+    #[test]
+    fn test_parse_images() {
+        let images = json!([{
+            "id": "1111",
+            "digest": "sha256:d",
+            "names": ["quay.io/app/web:2"],
+            "layer": "top",
+            "created": "2024-05-01T10:00:00Z"
+        }]);
+        let layers = json!([
+            { "id": "top", "parent": "base", "diff-digest": "sha256:b", "diff-size": 20 },
+            { "id": "base", "diff-digest": "sha256:a", "diff-size": 10 }
+        ]);
+        let found = parse_images(&images, &layers, DEFAULT_ROOT, "overlay");
+        assert_eq!(found.len(), 1);
+        let image = &found[0];
+        assert_eq!(image.id, "sha256:1111");
+        assert_eq!(image.tags, ["quay.io/app/web:2"]);
+        assert_eq!(image.digests, ["quay.io/app/web@sha256:d"]);
+        let digests: Vec<_> = image.layers.iter().map(|l| l.digest.as_str()).collect();
+        assert_eq!(digests, ["sha256:a", "sha256:b"]);
+        assert_eq!(
+            image.layers[0].path.as_deref(),
+            Some("/var/lib/containers/storage/overlay/base/diff")
+        );
+        assert_eq!(image.size(), 30);
+    }
+
+    #[test]
+    fn test_parse_containers() {
+        let containers = json!([{
+            "id": "c0ffee",
+            "names": ["web"],
+            "image": "1111",
+            "metadata": "{\"image-name\":\"quay.io/app/web:2\",\"name\":\"web\"}",
+            "created": "2024-05-01T10:00:00Z"
+        }]);
+        let found = parse_containers(&containers, DEFAULT_ROOT);
+        assert_eq!(found[0].name, "web");
+        assert_eq!(found[0].image, "quay.io/app/web:2");
+        assert_eq!(found[0].image_id.as_deref(), Some("sha256:1111"));
+    }
+}
@@ -0,0 +1,824 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Offline guest customization
+//!
+//! Customization changes a mounted guest the way an administrator would on
+//! its first boot, without booting it: hostname, users and their password
+//! hashes, SSH keys, files, first-boot scripts and cloud-init NoCloud seed
+//! data. User accounts are added by editing /etc/passwd, /etc/shadow and
+//! /etc/group directly, so nothing runs in the guest.
+//!
+//! Guests with SELinux enabled are relabeled on their next boot, since
+//! files written from the host have no labels.
+//!
+//! [`Guestfs::customize_ext4`] applies file writes and password changes to
+//! an ext4 root filesystem with the pure Rust writer instead, without
+//! launching or mounting anything.
+
+use crate::core::{Error, Result};
+use crate::disk::Ext4;
+use crate::guestfs::Guestfs;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+
+/// Where first-boot scripts wait to run
+pub const FIRSTBOOT_DIR: &str = "/usr/local/lib/guestkit/firstboot";
+
+/// systemd unit running the first-boot scripts
+const FIRSTBOOT_UNIT: &str = "guestkit-firstboot.service";
+
+/// Where cloud-init looks for NoCloud seed data
+pub const NOCLOUD_SEED_DIR: &str = "/var/lib/cloud/seed/nocloud";
+
+/// Lowest uid and gid of regular users
+const FIRST_USER_ID: u32 = 1000;
+
+/// uid and gid of nobody, never handed out
+const NOBODY_ID: u32 = 65534;
+
+const FIRSTBOOT_RUNNER: &str = r#"#!/bin/sh
+# Runs the scripts guestctl customize added, once each, in name order
+dir=/usr/local/lib/guestkit/firstboot
+mkdir -p "$dir/done"
+for script in "$dir"/*; do
+    [ -f "$script" ] || continue
+    echo "=== $(date) $script" >>/var/log/guestkit-firstboot.log
+    sh "$script" >>/var/log/guestkit-firstboot.log 2>&1
+    mv "$script" "$dir/done/"
+done
+"#;
+
+const FIRSTBOOT_SERVICE: &str = "[Unit]
+Description=guestkit first-boot scripts
+After=network-online.target
+Wants=network-online.target
+ConditionPathExists=/usr/local/lib/guestkit/firstboot.sh
+
+[Service]
+Type=oneshot
+ExecStart=/bin/sh /usr/local/lib/guestkit/firstboot.sh
+RemainAfterExit=yes
+TimeoutSec=0
+
+[Install]
+WantedBy=multi-user.target
+";
+
+/// One customization, applied in the order given
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(tag = "op", rename_all = "kebab-case")]
+pub enum CustomizeOp {
+    /// Set /etc/hostname and the 127.0.1.1 line of /etc/hosts
+    Hostname { hostname: String },
+    /// Add a user with a private group and a home directory from /etc/skel
+    User {
+        name: String,
+        #[serde(default)]
+        uid: Option<u32>,
+        /// Supplementary groups, which must exist
+        #[serde(default)]
+        groups: Vec<String>,
+        #[serde(default)]
+        shell: Option<String>,
+        /// crypt(3) hash; the account is locked without one
+        #[serde(default)]
+        password_hash: Option<String>,
+    },
+    /// Set the password hash of an existing user
+    PasswordHash { user: String, hash: String },
+    /// Append a public key to a user's authorized_keys
+    SshKey { user: String, key: String },
+    /// Write a file, creating its directory
+    WriteFile {
+        path: String,
+        content: String,
+        /// Permissions, 0644 if not given
+        #[serde(default)]
+        mode: Option<u32>,
+    },
+    /// Copy a host file into the guest
+    Upload {
+        source: String,
+        path: String,
+        #[serde(default)]
+        mode: Option<u32>,
+    },
+    /// Run a shell script once when the guest first boots (systemd)
+    FirstBoot { name: String, script: String },
+    /// Seed cloud-init's NoCloud datasource
+    CloudInit {
+        user_data: String,
+        /// instance-id and hostname; a new instance-id is made if not given
+        #[serde(default)]
+        meta_data: Option<String>,
+        #[serde(default)]
+        network_config: Option<String>,
+    },
+}
+
+impl fmt::Display for CustomizeOp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CustomizeOp::Hostname { hostname } => write!(f, "set hostname to {}", hostname),
+            CustomizeOp::User { name, groups, .. } if groups.is_empty() => {
+                write!(f, "add user {}", name)
+            }
+            CustomizeOp::User { name, groups, .. } => {
+                write!(f, "add user {} (groups {})", name, groups.join(","))
+            }
+            CustomizeOp::PasswordHash { user, .. } => write!(f, "set the password of {}", user),
+            CustomizeOp::SshKey { user, key } => {
+                let comment = key.split_whitespace().nth(2).unwrap_or("key");
+                write!(f, "authorize SSH {} for {}", comment, user)
+            }
+            CustomizeOp::WriteFile { path, .. } => write!(f, "write {}", path),
+            CustomizeOp::Upload { source, path, .. } => write!(f, "upload {} to {}", source, path),
+            CustomizeOp::FirstBoot { name, .. } => write!(f, "run {} on first boot", name),
+            CustomizeOp::CloudInit { .. } => write!(f, "seed cloud-init NoCloud data"),
+        }
+    }
+}
+
+/// What customization did
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct CustomizeReport {
+    pub changes: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+/// A line of /etc/passwd
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PasswdEntry {
+    pub name: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub home: String,
+    pub shell: String,
+}
+
+/// Accounts of /etc/passwd
+pub fn parse_passwd(content: &str) -> Vec<PasswdEntry> {
+    content
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split(':').collect();
+            if fields.len() < 7 || line.starts_with('#') {
+                return None;
+            }
+            Some(PasswdEntry {
+                name: fields[0].to_string(),
+                uid: fields[2].parse().ok()?,
+                gid: fields[3].parse().ok()?,
+                home: fields[5].to_string(),
+                shell: fields[6].to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Whether `hash` is a crypt(3) hash or a locked password, not clear text
+pub fn is_password_hash(hash: &str) -> bool {
+    if hash.starts_with('!') || hash.starts_with('*') {
+        return !hash.contains(':');
+    }
+    let fields: Vec<&str> = hash.split('$').collect();
+    hash.starts_with('$')
+        && fields.len() >= 4
+        && fields.iter().skip(1).all(|field| !field.is_empty())
+        && !hash.contains(':')
+        && !hash.contains(char::is_whitespace)
+}
+
+/// Lowest id of regular users not in `used`
+fn next_free_id(used: impl IntoIterator<Item = u32>) -> u32 {
+    let used: Vec<u32> = used.into_iter().collect();
+    (FIRST_USER_ID..NOBODY_ID)
+        .find(|id| !used.contains(id))
+        .unwrap_or(NOBODY_ID)
+}
+
+/// The account database files of a guest
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct AccountFiles {
+    pub passwd: String,
+    pub shadow: String,
+    pub group: String,
+    /// Not every distribution has /etc/gshadow
+    pub gshadow: Option<String>,
+}
+
+impl AccountFiles {
+    /// Add a user and its private group; returns its uid and gid
+    ///
+    /// `days` is the date of the password change, in days since 1970.
+    pub fn add_user(
+        &mut self,
+        name: &str,
+        uid: Option<u32>,
+        shell: &str,
+        password_hash: Option<&str>,
+        days: u64,
+    ) -> Result<(u32, u32)> {
+        let valid_name = !name.is_empty()
+            && !name.starts_with('-')
+            && name
+                .chars()
+                .all(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '.'));
+        if !valid_name {
+            return Err(Error::InvalidFormat(format!(
+                "Invalid user name '{}'",
+                name
+            )));
+        }
+        let accounts = parse_passwd(&self.passwd);
+        if accounts.iter().any(|account| account.name == name) {
+            return Err(Error::InvalidOperation(format!(
+                "User {} already exists",
+                name
+            )));
+        }
+        if group_line(&self.group, name).is_some() {
+            return Err(Error::InvalidOperation(format!(
+                "Group {} already exists",
+                name
+            )));
+        }
+
+        let uid = match uid {
+            Some(uid) if accounts.iter().any(|account| account.uid == uid) => {
+                return Err(Error::InvalidOperation(format!("uid {} is taken", uid)));
+            }
+            Some(uid) => uid,
+            None => next_free_id(accounts.iter().map(|account| account.uid)),
+        };
+        let gids: Vec<u32> = self
+            .group
+            .lines()
+            .filter_map(|line| line.split(':').nth(2)?.parse().ok())
+            .collect();
+        let gid = if gids.contains(&uid) {
+            next_free_id(gids)
+        } else {
+            uid
+        };
+
+        append_line(
+            &mut self.passwd,
+            &format!("{}:x:{}:{}::/home/{}:{}", name, uid, gid, name, shell),
+        );
+        append_line(
+            &mut self.shadow,
+            &format!(
+                "{}:{}:{}:0:99999:7:::",
+                name,
+                password_hash.unwrap_or("!"),
+                days
+            ),
+        );
+        append_line(&mut self.group, &format!("{}:x:{}:", name, gid));
+        if let Some(gshadow) = &mut self.gshadow {
+            append_line(gshadow, &format!("{}:!::", name));
+        }
+        Ok((uid, gid))
+    }
+
+    /// Add `user` to the member list of `group`
+    pub fn add_group_member(&mut self, group: &str, user: &str) -> Result<()> {
+        if group_line(&self.group, group).is_none() {
+            return Err(Error::NotFound(format!("Group {} not found", group)));
+        }
+        self.group = add_member(&self.group, group, 3, user);
+        if let Some(gshadow) = &self.gshadow {
+            if group_line(gshadow, group).is_some() {
+                self.gshadow = Some(add_member(gshadow, group, 3, user));
+            }
+        }
+        Ok(())
+    }
+
+    /// Set the password hash of `user`
+    pub fn set_password_hash(&mut self, user: &str, hash: &str, days: u64) -> Result<()> {
+        if !is_password_hash(hash) {
+            return Err(Error::InvalidFormat(format!(
+                "The password of {} is not a crypt(3) hash (generate one with \
+                 'openssl passwd -6')",
+                user
+            )));
+        }
+        let mut found = false;
+        let mut shadow = String::new();
+        for line in self.shadow.lines() {
+            let mut fields: Vec<String> = line.split(':').map(String::from).collect();
+            if fields.len() >= 3 && fields[0] == user {
+                fields[1] = hash.to_string();
+                fields[2] = days.to_string();
+                found = true;
+            }
+            shadow.push_str(&fields.join(":"));
+            shadow.push('\n');
+        }
+        if !found {
+            return Err(Error::NotFound(format!("User {} not in /etc/shadow", user)));
+        }
+        self.shadow = shadow;
+        Ok(())
+    }
+}
+
+fn group_line<'a>(content: &'a str, group: &str) -> Option<&'a str> {
+    content
+        .lines()
+        .find(|line| line.split(':').next() == Some(group))
+}
+
+/// `content` with `user` added to the comma-separated list in field `field`
+/// of the line of `group`
+fn add_member(content: &str, group: &str, field: usize, user: &str) -> String {
+    let mut edited = String::new();
+    for line in content.lines() {
+        let mut fields: Vec<String> = line.split(':').map(String::from).collect();
+        if fields[0] == group {
+            fields.resize(fields.len().max(field + 1), String::new());
+            let mut members: Vec<&str> = fields[field]
+                .split(',')
+                .filter(|member| !member.is_empty())
+                .collect();
+            if !members.contains(&user) {
+                members.push(user);
+            }
+            fields[field] = members.join(",");
+        }
+        edited.push_str(&fields.join(":"));
+        edited.push('\n');
+    }
+    edited
+}
+
+fn append_line(content: &mut String, line: &str) {
+    if !content.is_empty() && !content.ends_with('\n') {
+        content.push('\n');
+    }
+    content.push_str(line);
+    content.push('\n');
+}
+
+/// authorized_keys with `key` appended, or None if it is there already
+pub fn add_authorized_key(content: &str, key: &str) -> Option<String> {
+    let key = key.trim();
+    // Compare type and key material, not the comment
+    let material = |line: &str| -> Vec<String> {
+        line.split_whitespace()
+            .skip_while(|field| {
+                !field.starts_with("ssh-")
+                    && !field.starts_with("ecdsa-")
+                    && !field.starts_with("sk-")
+            })
+            .take(2)
+            .map(String::from)
+            .collect()
+    };
+    if content.lines().any(|line| material(line) == material(key)) {
+        return None;
+    }
+    let mut keys = content.to_string();
+    append_line(&mut keys, key);
+    Some(keys)
+}
+
+/// File name of the `index`th first-boot script
+fn firstboot_file_name(index: usize, name: &str) -> String {
+    let name: String = name
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '-' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    format!("{:02}-{}", index, name)
+}
+
+impl Guestfs {
+    /// Apply customizations to the guest, mounted read-write, in order
+    ///
+    /// Stops at the first customization that fails; the report lists what
+    /// was done until then.
+    pub fn customize(&mut self, root: &str, ops: &[CustomizeOp]) -> Result<CustomizeReport> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: customize {} ({} operations)", root, ops.len());
+        }
+
+        let mut report = CustomizeReport::default();
+        for op in ops {
+            self.customize_op(op, &mut report)
+                .map_err(|e| Error::InvalidOperation(format!("Failed to {}: {}", op, e)))?;
+            report.changes.push(capitalize(&op.to_string()));
+        }
+
+        if !ops.is_empty() && self.inspect_get_selinux_enabled(root).unwrap_or(false) {
+            self.touch("/.autorelabel")?;
+            report
+                .changes
+                .push("SELinux relabels the guest on its next boot".to_string());
+        }
+        Ok(report)
+    }
+
+    fn customize_op(&mut self, op: &CustomizeOp, report: &mut CustomizeReport) -> Result<()> {
+        let days = (chrono::Utc::now().timestamp() / 86400) as u64;
+        match op {
+            CustomizeOp::Hostname { hostname } => self.set_hostname(hostname),
+            CustomizeOp::User {
+                name,
+                uid,
+                groups,
+                shell,
+                password_hash,
+            } => {
+                if let Some(hash) = password_hash {
+                    if !is_password_hash(hash) {
+                        return Err(Error::InvalidFormat(
+                            "the password is not a crypt(3) hash".to_string(),
+                        ));
+                    }
+                }
+                let shell = match shell {
+                    Some(shell) => shell.clone(),
+                    None if self.exists("/bin/bash").unwrap_or(false) => "/bin/bash".to_string(),
+                    None => "/bin/sh".to_string(),
+                };
+
+                let mut accounts = self.account_files()?;
+                let (uid, gid) =
+                    accounts.add_user(name, *uid, &shell, password_hash.as_deref(), days)?;
+                for group in groups {
+                    accounts.add_group_member(group, name)?;
+                }
+                self.write_account_files(&accounts)?;
+
+                let home = format!("/home/{}", name);
+                self.mkdir_p(&home)?;
+                for entry in self.ls("/etc/skel").unwrap_or_default() {
+                    self.cp_r(&format!("/etc/skel/{}", entry), &home)?;
+                }
+                self.chown_recursive(uid as i32, gid as i32, &home)?;
+                self.chmod(0o700, &home)
+            }
+            CustomizeOp::PasswordHash { user, hash } => {
+                let mut accounts = self.account_files()?;
+                accounts.set_password_hash(user, hash, days)?;
+                self.write_account_files(&accounts)
+            }
+            CustomizeOp::SshKey { user, key } => {
+                let passwd = self.cat("/etc/passwd")?;
+                let account = parse_passwd(&passwd)
+                    .into_iter()
+                    .find(|account| &account.name == user)
+                    .ok_or_else(|| Error::NotFound(format!("User {} not found", user)))?;
+
+                let ssh_dir = format!("{}/.ssh", account.home.trim_end_matches('/'));
+                let keys_path = format!("{}/authorized_keys", ssh_dir);
+                self.mkdir_p(&ssh_dir)?;
+                let current = self.cat(&keys_path).unwrap_or_default();
+                if let Some(keys) = add_authorized_key(&current, key) {
+                    self.write(&keys_path, keys.as_bytes())?;
+                } else {
+                    report
+                        .warnings
+                        .push(format!("{} already has the SSH key", user));
+                }
+                self.chmod(0o700, &ssh_dir)?;
+                self.chmod(0o600, &keys_path)?;
+                self.chown_recursive(account.uid as i32, account.gid as i32, &ssh_dir)
+            }
+            CustomizeOp::WriteFile {
+                path,
+                content,
+                mode,
+            } => {
+                self.create_parent(path)?;
+                self.write(path, content.as_bytes())?;
+                self.chmod(mode.unwrap_or(0o644) as i32, path)
+            }
+            CustomizeOp::Upload { source, path, mode } => {
+                self.create_parent(path)?;
+                self.upload(source, path)?;
+                self.chmod(mode.unwrap_or(0o644) as i32, path)
+            }
+            CustomizeOp::FirstBoot { name, script } => self.add_firstboot(name, script),
+            CustomizeOp::CloudInit {
+                user_data,
+                meta_data,
+                network_config,
+            } => {
+                if !self.is_dir("/etc/cloud").unwrap_or(false) {
+                    report.warnings.push(
+                        "cloud-init is not installed in the guest; the seed data is unused"
+                            .to_string(),
+                    );
+                }
+                let meta_data = match meta_data {
+                    Some(meta_data) => meta_data.clone(),
+                    None => format!(
+                        "instance-id: iid-guestkit-{}\n",
+                        chrono::Utc::now().format("%Y%m%d%H%M%S")
+                    ),
+                };
+
+                self.mkdir_p(NOCLOUD_SEED_DIR)?;
+                let mut files = vec![("user-data", user_data.as_str()), ("meta-data", &meta_data)];
+                if let Some(network_config) = network_config {
+                    files.push(("network-config", network_config));
+                }
+                for (name, content) in files {
+                    let path = format!("{}/{}", NOCLOUD_SEED_DIR, name);
+                    self.write(&path, content.as_bytes())?;
+                    self.chmod(0o600, &path)?;
+                }
+                Ok(())
+            }
+        }
+    }
+
+    /// Apply customizations to the ext4 root filesystem on `device` with
+    /// the pure Rust writer, in order
+    ///
+    /// Supports file writes, uploads and password hashes; writes must be
+    /// enabled with [`Guestfs::set_enable_writes`].
+    pub fn customize_ext4(&mut self, device: &str, ops: &[CustomizeOp]) -> Result<CustomizeReport> {
+        if self.verbose {
+            eprintln!(
+                "guestfs: customize_ext4 {} ({} operations)",
+                device,
+                ops.len()
+            );
+        }
+
+        let mut fs = self.ext4_open(device, true)?;
+        let mut report = CustomizeReport::default();
+        for op in ops {
+            customize_ext4_op(&mut fs, op)
+                .map_err(|e| Error::InvalidOperation(format!("Failed to {}: {}", op, e)))?;
+            report.changes.push(capitalize(&op.to_string()));
+        }
+
+        let selinux = fs
+            .read_file("/etc/selinux/config")
+            .map(|config| selinux_enabled(&String::from_utf8_lossy(&config)))
+            .unwrap_or(false);
+        if !ops.is_empty() && selinux {
+            fs.write_file("/.autorelabel", b"", 0o644)?;
+            report
+                .changes
+                .push("SELinux relabels the guest on its next boot".to_string());
+        }
+        Ok(report)
+    }
+
+    fn account_files(&mut self) -> Result<AccountFiles> {
+        Ok(AccountFiles {
+            passwd: self.cat("/etc/passwd")?,
+            shadow: self.cat("/etc/shadow")?,
+            group: self.cat("/etc/group")?,
+            gshadow: self.cat("/etc/gshadow").ok(),
+        })
+    }
+
+    fn write_account_files(&mut self, accounts: &AccountFiles) -> Result<()> {
+        self.write("/etc/passwd", accounts.passwd.as_bytes())?;
+        self.write("/etc/shadow", accounts.shadow.as_bytes())?;
+        self.write("/etc/group", accounts.group.as_bytes())?;
+        if let Some(gshadow) = &accounts.gshadow {
+            self.write("/etc/gshadow", gshadow.as_bytes())?;
+        }
+        Ok(())
+    }
+
+    fn create_parent(&mut self, path: &str) -> Result<()> {
+        match path.rsplit_once('/') {
+            Some((parent, _)) if !parent.is_empty() => self.mkdir_p(parent),
+            _ => Ok(()),
+        }
+    }
+
+    /// Queue a first-boot script and enable the unit that runs it
+    fn add_firstboot(&mut self, name: &str, script: &str) -> Result<()> {
+        if !self.is_dir("/etc/systemd/system").unwrap_or(false) {
+            return Err(Error::Unsupported(
+                "first-boot scripts on guests without systemd".to_string(),
+            ));
+        }
+
+        self.mkdir_p(FIRSTBOOT_DIR)?;
+        let queued = self
+            .ls(FIRSTBOOT_DIR)?
+            .into_iter()
+            .filter(|entry| entry != "done")
+            .count();
+        let path = format!(
+            "{}/{}",
+            FIRSTBOOT_DIR,
+            firstboot_file_name(queued + 1, name)
+        );
+        self.write(&path, script.as_bytes())?;
+        self.chmod(0o755, &path)?;
+
+        let runner = "/usr/local/lib/guestkit/firstboot.sh";
+        self.write(runner, FIRSTBOOT_RUNNER.as_bytes())?;
+        self.chmod(0o755, runner)?;
+        let unit = format!("/etc/systemd/system/{}", FIRSTBOOT_UNIT);
+        self.write(&unit, FIRSTBOOT_SERVICE.as_bytes())?;
+        self.chmod(0o644, &unit)?;
+        self.mkdir_p("/etc/systemd/system/multi-user.target.wants")?;
+        self.ln_sf(
+            &unit,
+            &format!(
+                "/etc/systemd/system/multi-user.target.wants/{}",
+                FIRSTBOOT_UNIT
+            ),
+        )
+    }
+}
+
+fn customize_ext4_op(fs: &mut Ext4, op: &CustomizeOp) -> Result<()> {
+    match op {
+        CustomizeOp::WriteFile {
+            path,
+            content,
+            mode,
+        } => write_ext4_file(fs, path, content.as_bytes(), *mode),
+        CustomizeOp::Upload { source, path, mode } => {
+            let content = std::fs::read(source)?;
+            write_ext4_file(fs, path, &content, *mode)
+        }
+        CustomizeOp::PasswordHash { user, hash } => {
+            let days = (chrono::Utc::now().timestamp() / 86400) as u64;
+            let read = |fs: &Ext4, path| -> Result<String> {
+                Ok(String::from_utf8_lossy(&fs.read_file(path)?).into_owned())
+            };
+            let mut accounts = AccountFiles {
+                passwd: read(fs, "/etc/passwd")?,
+                shadow: read(fs, "/etc/shadow")?,
+                group: read(fs, "/etc/group")?,
+                gshadow: None,
+            };
+            accounts.set_password_hash(user, hash, days)?;
+            fs.write_file("/etc/shadow", accounts.shadow.as_bytes(), 0)
+        }
+        _ => Err(Error::Unsupported(format!(
+            "'{}' with the pure Rust ext4 writer",
+            op
+        ))),
+    }
+}
+
+/// Write a file, creating its directory, and set its permissions
+fn write_ext4_file(fs: &mut Ext4, path: &str, content: &[u8], mode: Option<u32>) -> Result<()> {
+    let mut dir = String::new();
+    let parents: Vec<&str> = path.split('/').filter(|name| !name.is_empty()).collect();
+    for name in parents.iter().take(parents.len().saturating_sub(1)) {
+        dir.push('/');
+        dir.push_str(name);
+        if !fs.exists(&dir)? {
+            fs.mkdir(&dir, 0o755)?;
+        }
+    }
+    fs.write_file(path, content, 0o644)?;
+    fs.chmod(path, mode.unwrap_or(0o644))
+}
+
+/// Whether an /etc/selinux/config enables SELinux
+fn selinux_enabled(config: &str) -> bool {
+    config
+        .lines()
+        .find_map(|line| line.strip_prefix("SELINUX="))
+        .is_some_and(|value| matches!(value.trim(), "enforcing" | "permissive"))
+}
+
+fn capitalize(text: &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const HASH: &str = "$6$salt$IxDD3jeSOb5eB1CX5LBsqZFVkJdido3OUILO5Ifz5iwMuTS4XMS130MTSuDDl3aCI6WouIL9AjRbLCelDCy.g.";
+
+    fn accounts() -> AccountFiles {
+        AccountFiles {
+            passwd: "root:x:0:0:root:/root:/bin/bash\n\
+                     alice:x:1000:1000::/home/alice:/bin/bash\n\
+                     nobody:x:65534:65534::/nonexistent:/usr/sbin/nologin\n"
+                .to_string(),
+            shadow: "root:*:19000:0:99999:7:::\nalice:!:19000:0:99999:7:::\n".to_string(),
+            group: "root:x:0:\nwheel:x:10:root\nalice:x:1000:\n".to_string(),
+            gshadow: Some("root:::\nwheel:::root\nalice:!::\n".to_string()),
+        }
+    }
+
+    #[test]
+    fn test_add_user() {
+        let mut files = accounts();
+        assert_eq!(
+            files
+                .add_user("deploy", None, "/bin/bash", Some(HASH), 20000)
+                .unwrap(),
+            (1001, 1001)
+        );
+        files.add_group_member("wheel", "deploy").unwrap();
+        assert!(files
+            .passwd
+            .ends_with("deploy:x:1001:1001::/home/deploy:/bin/bash\n"));
+        assert!(files
+            .shadow
+            .ends_with(&format!("deploy:{}:20000:0:99999:7:::\n", HASH)));
+        assert_eq!(
+            files.group,
+            "root:x:0:\nwheel:x:10:root,deploy\nalice:x:1000:\ndeploy:x:1001:\n"
+        );
+        assert_eq!(
+            files.gshadow.as_deref(),
+            Some("root:::\nwheel:::root,deploy\nalice:!::\ndeploy:!::\n")
+        );
+
+        assert!(files.add_user("alice", None, "/bin/sh", None, 0).is_err());
+        assert!(files.add_user("x:y", None, "/bin/sh", None, 0).is_err());
+        assert!(files
+            .add_user("bob", Some(1000), "/bin/sh", None, 0)
+            .is_err());
+        assert!(files.add_group_member("docker", "deploy").is_err());
+    }
+
+    #[test]
+    fn test_set_password_hash() {
+        let mut files = accounts();
+        files.set_password_hash("root", HASH, 20000).unwrap();
+        assert!(files
+            .shadow
+            .starts_with(&format!("root:{}:20000:0:99999:7:::\n", HASH)));
+
+        assert!(files.set_password_hash("root", "hunter2", 0).is_err());
+        assert!(files.set_password_hash("carol", HASH, 0).is_err());
+        assert!(is_password_hash("!"));
+        assert!(is_password_hash("$y$j9T$salt$hash"));
+        assert!(!is_password_hash("$6$"));
+    }
+
+    #[test]
+    fn test_add_authorized_key() {
+        let key = "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIK ops@example.com";
+        let keys = add_authorized_key("", key).unwrap();
+        assert_eq!(keys, format!("{}\n", key));
+        assert_eq!(
+            add_authorized_key(&keys, "ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIK other"),
+            None
+        );
+        assert_eq!(
+            add_authorized_key(
+                "from=\"10.0.0.0/8\" ssh-ed25519 AAAAC3NzaC1lZDI1NTE5AAAAIK",
+                key
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn test_customize_op_serde() {
+        let ops: Vec<CustomizeOp> = serde_json::from_str(
+            r#"[{"op": "hostname", "hostname": "web-01"},
+                {"op": "user", "name": "deploy", "groups": ["wheel"]},
+                {"op": "first-boot", "name": "register", "script": "echo hi"}]"#,
+        )
+        .unwrap();
+        assert_eq!(
+            ops[1],
+            CustomizeOp::User {
+                name: "deploy".to_string(),
+                uid: None,
+                groups: vec!["wheel".to_string()],
+                shell: None,
+                password_hash: None,
+            }
+        );
+        assert_eq!(ops[2].to_string(), "run register on first boot");
+        assert_eq!(firstboot_file_name(3, "register host"), "03-register_host");
+    }
+
+    #[test]
+    fn test_selinux_enabled() {
+        assert!(selinux_enabled(
+            "# comment\nSELINUX=enforcing\nSELINUXTYPE=targeted\n"
+        ));
+        assert!(selinux_enabled("SELINUX=permissive\n"));
+        assert!(!selinux_enabled("SELINUX=disabled\n"));
+        assert!(!selinux_enabled("SELINUXTYPE=targeted\n"));
+    }
+}
@@ -5,11 +5,57 @@ use crate::core::{Error, Result};
 use crate::disk::FileSystem;
 use crate::guestfs::Guestfs;
 use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+/// Partitions of a host disk as the kernel lists them in sysfs, by number
+pub(crate) fn sysfs_partitions(disk: &Path) -> Vec<(u32, PathBuf)> {
+    let Some(name) = disk.file_name().and_then(|name| name.to_str()) else {
+        return Vec::new();
+    };
+    let prefix = format!("{}p", name);
+    let Ok(entries) = std::fs::read_dir(Path::new("/sys/class/block").join(name)) else {
+        return Vec::new();
+    };
+    let mut partitions: Vec<(u32, PathBuf)> = entries
+        .filter_map(|entry| entry.ok()?.file_name().into_string().ok())
+        .filter_map(|entry| {
+            let number = entry.strip_prefix(&prefix)?.parse().ok()?;
+            Some((number, Path::new("/dev").join(&entry)))
+        })
+        .collect();
+    partitions.sort();
+    partitions
+}
+
+/// Value of a blkid tag (TYPE, UUID, LABEL) of a host block device
+fn blkid_value(path: &Path, tag: &str) -> Result<Option<String>> {
+    let need_sudo = unsafe { libc::geteuid() } != 0;
+
+    let mut cmd = if need_sudo {
+        let mut sudo_cmd = std::process::Command::new("sudo");
+        sudo_cmd.arg("blkid");
+        sudo_cmd
+    } else {
+        std::process::Command::new("blkid")
+    };
+
+    let output = cmd
+        .arg("-o")
+        .arg("value")
+        .arg("-s")
+        .arg(tag)
+        .arg(path)
+        .output()
+        .map_err(|e| Error::CommandFailed(format!("Failed to run blkid: {}", e)))?;
+
+    let value = String::from_utf8_lossy(&output.stdout).trim().to_string();
+    Ok((output.status.success() && !value.is_empty()).then_some(value))
+}
 
 impl Guestfs {
     /// List all block devices
     ///
-    pub fn list_devices(&self) -> Result<Vec<String>> {
+    pub(crate) fn list_devices_untraced(&self) -> Result<Vec<String>> {
         self.ensure_ready()?;
 
         // Return list of drives added
@@ -23,7 +69,7 @@ impl Guestfs {
 
     /// List all partitions
     ///
-    pub fn list_partitions(&self) -> Result<Vec<String>> {
+    pub(crate) fn list_partitions_untraced(&self) -> Result<Vec<String>> {
         self.ensure_ready()?;
 
         let partition_table = self.partition_table()?;
@@ -32,13 +78,18 @@ impl Guestfs {
         for partition in partition_table.partitions() {
             partitions.push(format!("/dev/sda{}", partition.number));
         }
+        partitions.extend(
+            self.extra_drive_partitions()
+                .into_iter()
+                .map(|(device, _)| device),
+        );
 
         Ok(partitions)
     }
 
     /// List all filesystems detected
     ///
-    pub fn list_filesystems(&mut self) -> Result<HashMap<String, String>> {
+    pub(crate) fn list_filesystems_untraced(&mut self) -> Result<HashMap<String, String>> {
         self.ensure_ready()?;
 
         let mut filesystems = HashMap::new();
@@ -52,6 +103,25 @@ impl Guestfs {
         for partition in &partitions {
             let device_name = format!("/dev/sda{}", partition.number);
 
+            // Unlocked BitLocker and LUKS volumes are listed by their mapped
+            // device, locked ones as "BitLocker" and "crypto_LUKS"
+            if let Some(mapper) = self
+                .bitlocker_mapping(&device_name)
+                .or_else(|| self.luks_mapping(&device_name))
+            {
+                let fs_type = self.vfs_type(&mapper)?;
+                filesystems.insert(mapper, fs_type);
+                continue;
+            }
+            if matches!(self.bitlocker_detect(&device_name), Ok(Some(_))) {
+                filesystems.insert(device_name, "BitLocker".to_string());
+                continue;
+            }
+            if matches!(self.luks_detect(&device_name), Ok(Some(_))) {
+                filesystems.insert(device_name, "crypto_LUKS".to_string());
+                continue;
+            }
+
             let reader = self
                 .reader
                 .as_mut()
@@ -69,6 +139,8 @@ impl Guestfs {
                     crate::disk::FileSystemType::HfsPlus => "hfsplus",
                     crate::disk::FileSystemType::Apfs => "apfs",
                     crate::disk::FileSystemType::Iso9660 => "iso9660",
+                    crate::disk::FileSystemType::Squashfs => "squashfs",
+                    crate::disk::FileSystemType::Erofs => "erofs",
                     crate::disk::FileSystemType::Swap => "swap",
                     crate::disk::FileSystemType::Unknown => "unknown",
                 };
@@ -77,6 +149,18 @@ impl Guestfs {
             }
         }
 
+        // Partitions of further drives, so fstab can map mountpoints to them
+        for (device, _) in self.extra_drive_partitions() {
+            let fs_type = self.vfs_type(&device)?;
+            filesystems.insert(device, fs_type);
+        }
+
+        // md arrays assembled from the drives
+        for md in self.md_arrays.clone() {
+            let fs_type = self.vfs_type(&md)?;
+            filesystems.insert(md, fs_type);
+        }
+
         Ok(filesystems)
     }
 
@@ -85,42 +169,26 @@ impl Guestfs {
     pub fn vfs_type(&mut self, device: &str) -> Result<String> {
         self.ensure_ready()?;
 
-        // For LVM volumes (/dev/mapper/* or /dev/vgname/lvname), use blkid directly
-        if device.starts_with("/dev/mapper/") || (device.starts_with("/dev/") && device.matches('/').count() >= 3) {
-            // Use blkid to detect filesystem type on LVM volumes
-            let need_sudo = unsafe { libc::geteuid() } != 0;
-
-            let mut cmd = if need_sudo {
-                let mut sudo_cmd = std::process::Command::new("sudo");
-                sudo_cmd.arg("blkid");
-                sudo_cmd
-            } else {
-                std::process::Command::new("blkid")
-            };
-
-            let output = cmd
-                .arg("-o")
-                .arg("value")
-                .arg("-s")
-                .arg("TYPE")
-                .arg(device)
-                .output()
-                .map_err(|e| Error::CommandFailed(format!("Failed to run blkid: {}", e)))?;
-
-            if output.status.success() {
-                let fs_type = String::from_utf8_lossy(&output.stdout).trim().to_string();
-                if !fs_type.is_empty() {
-                    return Ok(fs_type);
-                }
-            }
+        if super::btrfs::parse_btrfsvol(device).is_some() {
+            return Ok("btrfs".to_string());
+        }
 
-            // If blkid fails, return unknown
-            return Ok("unknown".to_string());
+        // LVM volumes, md arrays and partitions of further drives are
+        // probed by the kernel's device node
+        if let Some(path) = self.direct_device_path(device) {
+            return Ok(blkid_value(&path, "TYPE")?.unwrap_or_else(|| "unknown".to_string()));
         }
 
         // For regular partitions, use the existing detection logic
         let partition_num = self.parse_device_name(device)?;
 
+        if self.bitlocker_detect(device)?.is_some() {
+            return Ok("BitLocker".to_string());
+        }
+        if self.luks_detect(device)?.is_some() {
+            return Ok("crypto_LUKS".to_string());
+        }
+
         // Clone partition to avoid borrow checker issues
         let partition = {
             let partition_table = self.partition_table()?;
@@ -150,6 +218,8 @@ impl Guestfs {
             crate::disk::FileSystemType::HfsPlus => "hfsplus",
             crate::disk::FileSystemType::Apfs => "apfs",
             crate::disk::FileSystemType::Iso9660 => "iso9660",
+            crate::disk::FileSystemType::Squashfs => "squashfs",
+            crate::disk::FileSystemType::Erofs => "erofs",
             crate::disk::FileSystemType::Swap => "swap",
             crate::disk::FileSystemType::Unknown => "unknown",
         };
@@ -159,9 +229,14 @@ impl Guestfs {
 
     /// Get filesystem label
     ///
-    pub fn vfs_label(&mut self, device: &str) -> Result<String> {
+    pub(crate) fn vfs_label_untraced(&mut self, device: &str) -> Result<String> {
         self.ensure_ready()?;
 
+        if let Some(path) = self.direct_device_path(device) {
+            return blkid_value(&path, "LABEL")?
+                .ok_or_else(|| Error::NotFound("No label".to_string()));
+        }
+
         let partition_num = self.parse_device_name(device)?;
 
         // Clone partition to avoid borrow checker issues
@@ -188,9 +263,14 @@ impl Guestfs {
 
     /// Get filesystem UUID
     ///
-    pub fn vfs_uuid(&mut self, device: &str) -> Result<String> {
+    pub(crate) fn vfs_uuid_untraced(&mut self, device: &str) -> Result<String> {
         self.ensure_ready()?;
 
+        if let Some(path) = self.direct_device_path(device) {
+            return blkid_value(&path, "UUID")?
+                .ok_or_else(|| Error::NotFound("No UUID".to_string()));
+        }
+
         let partition_num = self.parse_device_name(device)?;
 
         // Clone partition to avoid borrow checker issues
@@ -217,9 +297,14 @@ impl Guestfs {
 
     /// Get block device size in bytes
     ///
-    pub fn blockdev_getsize64(&self, device: &str) -> Result<i64> {
+    pub(crate) fn blockdev_getsize64_untraced(&self, device: &str) -> Result<i64> {
         self.ensure_ready()?;
 
+        if let Some(path) = self.direct_device_path(device) {
+            let mut file = std::fs::File::open(path)?;
+            return Ok(std::io::Seek::seek(&mut file, std::io::SeekFrom::End(0))? as i64);
+        }
+
         let partition_num = self.parse_device_name(device)?;
 
         if partition_num == 0 {
@@ -296,6 +381,94 @@ impl Guestfs {
             .map(|c| c.is_numeric())
             .unwrap_or(false))
     }
+
+    /// Byte offset of a guest device within the disk image (internal)
+    pub(crate) fn partition_offset(&mut self, device: &str) -> Result<u64> {
+        let partition_num = self.parse_device_name(device)?;
+        if partition_num == 0 {
+            return Ok(0);
+        }
+
+        self.partition_table()?
+            .partitions()
+            .iter()
+            .find(|p| p.number == partition_num)
+            .map(|p| p.start_lba * 512)
+            .ok_or_else(|| Error::NotFound(format!("Partition {} not found", partition_num)))
+    }
+
+    /// Partitions of further drives: guest name (`/dev/sdb1`) and host device
+    pub(crate) fn extra_drive_partitions(&self) -> Vec<(String, PathBuf)> {
+        let mut partitions = Vec::new();
+        for (index, extra) in self.extra_drives.iter().enumerate() {
+            let Some(disk) = extra.device_path() else {
+                continue;
+            };
+            let letter = (b'b' + index as u8) as char;
+            for (number, path) in sysfs_partitions(disk) {
+                partitions.push((format!("/dev/sd{}{}", letter, number), path));
+            }
+        }
+        partitions
+    }
+
+    /// Host device node of a device the kernel probes directly
+    ///
+    /// LVM and device-mapper volumes and md arrays are used as they are,
+    /// further drives (`/dev/sdb`, `/dev/sdb1`) map to their loop or NBD
+    /// device. Devices on the first drive are read through the partition
+    /// table instead and give `None`.
+    pub(crate) fn direct_device_path(&self, device: &str) -> Option<PathBuf> {
+        if device.starts_with("/dev/mapper/")
+            || (device.starts_with("/dev/") && device.matches('/').count() >= 3)
+        {
+            return Some(PathBuf::from(device));
+        }
+
+        let rest = device.strip_prefix("/dev/sd")?;
+        let letter = *rest.as_bytes().first()?;
+        if !(b'b'..=b'z').contains(&letter) {
+            return None;
+        }
+        let disk = self
+            .extra_drives
+            .get((letter - b'b') as usize)?
+            .device_path()?;
+        match &rest[1..] {
+            "" => Some(disk.to_path_buf()),
+            number => {
+                let number: u32 = number.parse().ok()?;
+                sysfs_partitions(disk)
+                    .into_iter()
+                    .find(|(n, _)| *n == number)
+                    .map(|(_, path)| path)
+            }
+        }
+    }
+
+    /// Host block device backing a guest device (loop or NBD)
+    pub(crate) fn host_device_path(&self, device: &str) -> Result<PathBuf> {
+        let partition_num = self.parse_device_name(device)?;
+
+        if let Some(loop_dev) = &self.loop_device {
+            let path = if partition_num > 0 {
+                loop_dev.partition_path(partition_num)
+            } else {
+                loop_dev.device_path().map(|p| p.to_path_buf())
+            };
+            path.ok_or_else(|| Error::InvalidState("Loop device not connected".to_string()))
+        } else if let Some(nbd) = &self.nbd_device {
+            Ok(if partition_num > 0 {
+                nbd.partition_path(partition_num)
+            } else {
+                nbd.device_path().to_path_buf()
+            })
+        } else {
+            Err(Error::InvalidState(
+                "No block device available (neither loop nor NBD)".to_string(),
+            ))
+        }
+    }
 }
 
 #[cfg(test)]
@@ -329,4 +502,21 @@ mod tests {
         assert_eq!(g.device_index("/dev/sdb").unwrap(), 1);
         assert_eq!(g.device_index("/dev/vda").unwrap(), 0);
     }
+
+    #[test]
+    fn test_direct_device_path() {
+        let g = Guestfs::new().unwrap();
+
+        assert_eq!(
+            g.direct_device_path("/dev/mapper/rhel-root"),
+            Some(PathBuf::from("/dev/mapper/rhel-root"))
+        );
+        assert_eq!(
+            g.direct_device_path("/dev/md/guestctl1_0"),
+            Some(PathBuf::from("/dev/md/guestctl1_0"))
+        );
+        assert_eq!(g.direct_device_path("/dev/sda1"), None);
+        // No further drives attached
+        assert_eq!(g.direct_device_path("/dev/sdb1"), None);
+    }
 }
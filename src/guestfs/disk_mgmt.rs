@@ -97,6 +97,34 @@ impl Guestfs {
         Ok(stdout.contains("backing file"))
     }
 
+    /// Get the backing file of a disk image, if it has one
+    ///
+    /// Relative backing file names are resolved against the image's directory.
+    pub fn disk_backing_file(&mut self, filename: &str) -> Result<Option<String>> {
+        if self.verbose {
+            eprintln!("guestfs: disk_backing_file {}", filename);
+        }
+
+        let output = Command::new("qemu-img")
+            .arg("info")
+            .arg("--output=json")
+            .arg(filename)
+            .output()
+            .map_err(|e| Error::CommandFailed(format!("Failed to execute qemu-img: {}", e)))?;
+
+        if !output.status.success() {
+            return Err(Error::CommandFailed(format!(
+                "qemu-img info failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+
+        let info: serde_json::Value = serde_json::from_slice(&output.stdout)
+            .map_err(|e| Error::CommandFailed(format!("Invalid qemu-img output: {}", e)))?;
+
+        Ok(backing_file_from_info(&info, filename))
+    }
+
     /// Get virtual size of disk image
     ///
     pub fn disk_virtual_size(&mut self, filename: &str) -> Result<i64> {
@@ -291,6 +319,21 @@ impl Guestfs {
     }
 }
 
+/// Backing file named in `qemu-img info --output=json` output
+fn backing_file_from_info(info: &serde_json::Value, filename: &str) -> Option<String> {
+    if let Some(full) = info.get("full-backing-filename").and_then(|v| v.as_str()) {
+        return Some(full.to_string());
+    }
+
+    let backing = info.get("backing-filename").and_then(|v| v.as_str())?;
+    let path = std::path::Path::new(backing);
+    if path.is_absolute() {
+        return Some(backing.to_string());
+    }
+    let dir = std::path::Path::new(filename).parent()?;
+    Some(dir.join(path).display().to_string())
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -300,4 +343,26 @@ mod tests {
         let mut g = Guestfs::new().unwrap();
         // API structure tests
     }
+
+    #[test]
+    fn test_backing_file_from_info() {
+        let info = serde_json::json!({
+            "format": "qcow2",
+            "backing-filename": "base.qcow2",
+            "full-backing-filename": "/var/lib/images/base.qcow2"
+        });
+        assert_eq!(
+            backing_file_from_info(&info, "/tmp/overlay.qcow2").as_deref(),
+            Some("/var/lib/images/base.qcow2")
+        );
+
+        let info = serde_json::json!({ "backing-filename": "base.qcow2" });
+        assert_eq!(
+            backing_file_from_info(&info, "/srv/vm/overlay.qcow2").as_deref(),
+            Some("/srv/vm/base.qcow2")
+        );
+
+        let info = serde_json::json!({ "format": "raw" });
+        assert_eq!(backing_file_from_info(&info, "disk.img"), None);
+    }
 }
@@ -4,6 +4,7 @@
 //! This implementation provides ext-specific functionality.
 
 use crate::core::{Error, Result};
+use crate::disk::{DiskReader, Ext4, PartitionTable};
 use crate::guestfs::Guestfs;
 use std::process::Command;
 
@@ -382,6 +383,170 @@ impl Guestfs {
 
         Ok(())
     }
+
+    /// Open the ext2/3/4 filesystem on `device` of the first drive with the
+    /// pure Rust reader, for writing if `write`
+    ///
+    /// Needs no launch and mounts nothing. Writes need a raw image added
+    /// read-write, and [`Guestfs::set_enable_writes`].
+    pub fn ext4_open(&self, device: &str, write: bool) -> Result<Ext4> {
+        if write && !self.enable_writes {
+            return Err(Error::PermissionDenied(
+                "pure Rust ext4 writes are disabled; enable them with set_enable_writes \
+                 (--enable-writes)"
+                    .to_string(),
+            ));
+        }
+        let (reader, offset) = self.open_partition(device)?;
+        if !write {
+            return Ext4::from_reader(reader, offset);
+        }
+        let drive = &self.drives[0];
+        if self.readonly || drive.readonly {
+            return Err(Error::PermissionDenied(format!(
+                "{} was added read-only",
+                drive.path.display()
+            )));
+        }
+        if reader.raw_file().is_none() {
+            return Err(Error::Unsupported(format!(
+                "pure Rust ext4 writes to {:?} images; convert {} to raw first",
+                reader.format(),
+                drive.path.display()
+            )));
+        }
+        drop(reader);
+        Ext4::open_rw(&drive.path, offset)
+    }
+
+    /// The first drive, and where partition `device` starts on it, for the
+    /// pure Rust filesystem layers
+    pub(crate) fn open_partition(&self, device: &str) -> Result<(DiskReader, u64)> {
+        let drive = self
+            .drives
+            .first()
+            .ok_or_else(|| Error::InvalidState("No drives added".to_string()))?;
+        if self.mounted.contains_key(device) {
+            return Err(Error::InvalidState(format!(
+                "{} is mounted; unmount it first",
+                device
+            )));
+        }
+        if device.starts_with("/dev/mapper/") || device.matches('/').count() >= 3 {
+            return Err(Error::Unsupported(format!(
+                "{}: the pure Rust filesystem layers only open partitions of the first drive",
+                device
+            )));
+        }
+
+        let partition = self.parse_device_name(device)?;
+        let mut reader = DiskReader::open(&drive.path)?;
+        let offset = if partition == 0 {
+            0
+        } else {
+            let table = PartitionTable::parse(&mut reader)?;
+            let part = table
+                .partitions()
+                .iter()
+                .find(|part| part.number == partition)
+                .ok_or_else(|| Error::NotFound(format!("Partition {} not found", device)))?;
+            part.start_lba * table.sector_size()
+        };
+        Ok((reader, offset))
+    }
+
+    /// Find the ext2/3/4 root filesystem of the first drive with the pure
+    /// Rust reader: the first one holding /etc/os-release
+    pub fn ext4_find_root(&self) -> Result<String> {
+        let drive = self
+            .drives
+            .first()
+            .ok_or_else(|| Error::InvalidState("No drives added".to_string()))?;
+        let table = PartitionTable::parse(&mut DiskReader::open(&drive.path)?)?;
+        let devices = table
+            .partitions()
+            .iter()
+            .map(|part| format!("/dev/sda{}", part.number))
+            .chain(std::iter::once("/dev/sda".to_string()));
+        for device in devices {
+            let Ok(fs) = self.ext4_open(&device, false) else {
+                continue;
+            };
+            if fs.exists("/etc/os-release").unwrap_or(false) {
+                return Ok(device);
+            }
+        }
+        Err(Error::NotFound(
+            "No ext2/3/4 root filesystem with /etc/os-release found".to_string(),
+        ))
+    }
+
+    /// Read a file of the ext2/3/4 filesystem on `device`, without mounting
+    ///
+    pub fn ext4_read_file(&mut self, device: &str, path: &str) -> Result<Vec<u8>> {
+        if self.verbose {
+            eprintln!("guestfs: ext4_read_file {} {}", device, path);
+        }
+
+        self.ext4_open(device, false)?.read_file(path)
+    }
+
+    /// Create or replace a file on the ext4 filesystem on `device`, without
+    /// mounting; new files get mode 0644
+    ///
+    pub fn ext4_write(&mut self, device: &str, path: &str, content: &[u8]) -> Result<()> {
+        if self.verbose {
+            eprintln!("guestfs: ext4_write {} {}", device, path);
+        }
+
+        self.ext4_open(device, true)?
+            .write_file(path, content, 0o644)
+    }
+
+    /// Create a directory on the ext4 filesystem on `device`, without
+    /// mounting
+    ///
+    pub fn ext4_mkdir(&mut self, device: &str, path: &str, mode: i32) -> Result<()> {
+        if self.verbose {
+            eprintln!("guestfs: ext4_mkdir {} {} {:o}", device, path, mode);
+        }
+
+        self.ext4_open(device, true)?.mkdir(path, mode as u32)
+    }
+
+    /// Remove a file on the ext4 filesystem on `device`, without mounting
+    ///
+    pub fn ext4_rm(&mut self, device: &str, path: &str) -> Result<()> {
+        if self.verbose {
+            eprintln!("guestfs: ext4_rm {} {}", device, path);
+        }
+
+        self.ext4_open(device, true)?.unlink(path)
+    }
+
+    /// Change the permissions of a file on the ext4 filesystem on `device`,
+    /// without mounting
+    ///
+    pub fn ext4_chmod(&mut self, device: &str, mode: i32, path: &str) -> Result<()> {
+        if self.verbose {
+            eprintln!("guestfs: ext4_chmod {} {:o} {}", device, mode, path);
+        }
+
+        self.ext4_open(device, true)?.chmod(path, mode as u32)
+    }
+
+    /// Truncate or extend a file on the ext4 filesystem on `device`, without
+    /// mounting
+    ///
+    pub fn ext4_truncate_size(&mut self, device: &str, path: &str, size: i64) -> Result<()> {
+        if self.verbose {
+            eprintln!("guestfs: ext4_truncate_size {} {} {}", device, path, size);
+        }
+
+        let size = u64::try_from(size)
+            .map_err(|_| Error::InputValidation(format!("Invalid size: {}", size)))?;
+        self.ext4_open(device, true)?.truncate(path, size)
+    }
 }
 
 #[cfg(test)]
@@ -393,4 +558,19 @@ mod tests {
         let mut g = Guestfs::new().unwrap();
         // API structure tests
     }
+
+    #[test]
+    fn test_ext4_writes_need_enabling() {
+        let mut g = Guestfs::new().unwrap();
+        assert!(!g.get_enable_writes());
+        assert!(matches!(
+            g.ext4_write("/dev/sda1", "/etc/hostname", b"web01\n"),
+            Err(Error::PermissionDenied(_))
+        ));
+        g.set_enable_writes(true);
+        assert!(matches!(
+            g.ext4_write("/dev/sda1", "/etc/hostname", b"web01\n"),
+            Err(Error::InvalidState(_))
+        ));
+    }
 }
@@ -13,9 +13,15 @@ use std::path::{Path, PathBuf};
 
 impl Guestfs {
     /// Find the root mountpoint (internal helper)
-    fn find_root_mountpoint(&self) -> Result<&str> {
-        self.mounted
-            .get("/dev/sda1")
+    pub(crate) fn find_root_mountpoint(&self) -> Result<&str> {
+        // Filesystems mounted at / of the mount root come before guesses
+        let at_mount_root = self.mount_root.as_ref().and_then(|mount_root| {
+            self.mounted
+                .values()
+                .find(|mountpoint| Path::new(mountpoint.as_str()) == mount_root)
+        });
+        at_mount_root
+            .or_else(|| self.mounted.get("/dev/sda1"))
             .or_else(|| self.mounted.get("/dev/sda2"))
             .or_else(|| self.mounted.get("/dev/vda1"))
             .or_else(|| self.mounted.values().next())
@@ -44,15 +50,25 @@ impl Guestfs {
         let candidate_path = PathBuf::from(root_mountpoint).join(guest_path_clean);
 
         // 4. Canonicalize path to resolve symlinks and get absolute path
-        // Note: canonicalize() requires the path to exist
-        let canonical = candidate_path.canonicalize().map_err(|e| {
-            // If path doesn't exist, return NotFound instead of generic error
-            if e.kind() == std::io::ErrorKind::NotFound {
-                Error::NotFound(format!("Path does not exist: {}", guest_path))
-            } else {
-                Error::Io(e)
+        // Note: canonicalize() requires the path to exist, so a path to be
+        // created resolves through its parent directory
+        let not_found = || Error::NotFound(format!("Path does not exist: {}", guest_path));
+        let canonical = match candidate_path.canonicalize() {
+            Ok(canonical) => canonical,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                // A dangling symlink would be followed on write
+                if candidate_path.symlink_metadata().is_ok() {
+                    return Err(not_found());
+                }
+                match (candidate_path.parent(), candidate_path.file_name()) {
+                    (Some(parent), Some(name)) => {
+                        parent.canonicalize().map_err(|_| not_found())?.join(name)
+                    }
+                    _ => return Err(not_found()),
+                }
             }
-        })?;
+            Err(e) => return Err(Error::Io(e)),
+        };
 
         // 5. Get canonical root for security check
         let root_canonical = PathBuf::from(root_mountpoint).canonicalize().map_err(|e| {
@@ -183,7 +199,7 @@ impl Guestfs {
 
     /// List directory contents
     ///
-    pub fn ls(&mut self, directory: &str) -> Result<Vec<String>> {
+    pub(crate) fn ls_untraced(&mut self, directory: &str) -> Result<Vec<String>> {
         self.ensure_ready()?;
 
         if self.verbose {
@@ -507,6 +523,36 @@ impl Guestfs {
         Ok(())
     }
 
+    /// Copy a file or directory tree from the guest into host directory `localdir`
+    ///
+    /// Ownership, permissions and timestamps are kept where the host allows.
+    pub fn copy_out(&mut self, remotepath: &str, localdir: &str) -> Result<()> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: copy_out {} {}", remotepath, localdir);
+        }
+
+        let guest_path = self.resolve_guest_path(remotepath)?;
+        let output = std::process::Command::new("cp")
+            .arg("-a")
+            .arg(&guest_path)
+            .arg(localdir)
+            .output()
+            .map_err(|e| Error::CommandFailed(format!("Failed to execute cp: {}", e)))?;
+
+        if !output.status.success() {
+            return Err(Error::CommandFailed(format!(
+                "Failed to copy {} out to {}: {}",
+                remotepath,
+                localdir,
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+
+        Ok(())
+    }
+
     /// Upload file from host to guest
     ///
     pub fn upload(&mut self, filename: &str, remotefilename: &str) -> Result<()> {
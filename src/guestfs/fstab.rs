@@ -10,14 +10,20 @@
 //! - Btrfs with subvolumes → proper subvol= options
 //! - LUKS encrypted devices → crypttab with UUID
 //! - Swap → UUID
+//! - UUID=, LABEL=, PARTUUID= and PARTLABEL= specs that resolve → kept
 //!
-//! Never keeps: /dev/sdX, /dev/vdX, /dev/nbdX, /dev/disk/by-path/*
+//! Never keeps: /dev/sdX, /dev/vdX, /dev/xvdX, /dev/nvmeXnYpZ, /dev/nbdX,
+//! /dev/disk/by-path/*. Device paths the inventory does not know are renamed
+//! for the target hypervisor with a [`DeviceTranslation`] instead.
 
 use crate::core::Result;
+use crate::guestfs::btrfs::{btrfsvol_mountable, subvol_option};
 use crate::guestfs::device_inventory::{Inventory, find_by_spec};
 use std::collections::HashMap;
+use std::fmt;
 use std::fs;
 use std::path::Path;
+use std::str::FromStr;
 
 /// fstab entry
 #[derive(Debug, Clone)]
@@ -38,7 +44,7 @@ pub struct FstabEntry {
 
 impl FstabEntry {
     /// Parse an fstab line
-    fn parse(line: &str) -> Option<Self> {
+    pub(crate) fn parse(line: &str) -> Option<Self> {
         let trimmed = line.trim();
 
         // Skip comments and empty lines
@@ -61,8 +67,18 @@ impl FstabEntry {
         })
     }
 
+    /// Mountable for the filesystem of this entry on `device`
+    ///
+    /// Btrfs entries with `subvol=` mount that subvolume of the device.
+    pub(crate) fn mountable(&self, device: String) -> String {
+        match subvol_option(&self.options) {
+            Some(subvolume) if self.fstype == "btrfs" => btrfsvol_mountable(&device, subvolume),
+            _ => device,
+        }
+    }
+
     /// Format as fstab line
-    fn format(&self) -> String {
+    pub(crate) fn format(&self) -> String {
         format!(
             "{}\t{}\t{}\t{}\t{}\t{}\n",
             self.spec, self.mountpoint, self.fstype, self.options, self.dump, self.pass
@@ -71,9 +87,11 @@ impl FstabEntry {
 
     /// Check if spec should be rewritten (is it a device path we want to avoid?)
     fn needs_rewrite(&self) -> bool {
-        // Rewrite /dev/sd*, /dev/vd*, /dev/nbd*, /dev/disk/by-path/*
+        // Rewrite /dev/sd*, /dev/vd*, /dev/xvd*, /dev/nvme*, /dev/nbd*, /dev/disk/by-path/*
         if self.spec.starts_with("/dev/sd")
             || self.spec.starts_with("/dev/vd")
+            || self.spec.starts_with("/dev/xvd")
+            || self.spec.starts_with("/dev/nvme")
             || self.spec.starts_with("/dev/nbd")
             || self.spec.starts_with("/dev/disk/by-path/")
             || self.spec.starts_with("/dev/hd")
@@ -171,20 +189,264 @@ pub fn set_mount_option(opts: &str, key: &str, kv: &str) -> String {
     }
 }
 
+/// Specs that name a filesystem or partition rather than a device path
+const PORTABLE_SPECS: [&str; 4] = ["UUID=", "LABEL=", "PARTUUID=", "PARTLABEL="];
+
+/// Disk naming of a hypervisor's storage bus
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DeviceNaming {
+    /// /dev/sdX: SATA, SCSI, virtio-scsi, Hyper-V, VMware
+    Scsi,
+    /// /dev/vdX: virtio-blk
+    Virtio,
+    /// /dev/xvdX: Xen
+    Xen,
+    /// /dev/hdX: legacy IDE
+    Ide,
+    /// /dev/nvmeXn1: NVMe, one controller per disk
+    Nvme,
+}
+
+impl DeviceNaming {
+    const PREFIXES: [(&'static str, DeviceNaming); 4] = [
+        ("/dev/sd", DeviceNaming::Scsi),
+        ("/dev/vd", DeviceNaming::Virtio),
+        ("/dev/xvd", DeviceNaming::Xen),
+        ("/dev/hd", DeviceNaming::Ide),
+    ];
+}
+
+impl FromStr for DeviceNaming {
+    type Err = crate::core::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "sd" | "scsi" | "sata" => Ok(DeviceNaming::Scsi),
+            "vd" | "virtio" => Ok(DeviceNaming::Virtio),
+            "xvd" | "xen" => Ok(DeviceNaming::Xen),
+            "hd" | "ide" => Ok(DeviceNaming::Ide),
+            "nvme" => Ok(DeviceNaming::Nvme),
+            _ => Err(crate::core::Error::InvalidFormat(format!(
+                "Unknown device naming '{}' (sd, vd, xvd, hd, nvme)",
+                s
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for DeviceNaming {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DeviceNaming::Scsi => "sd",
+            DeviceNaming::Virtio => "vd",
+            DeviceNaming::Xen => "xvd",
+            DeviceNaming::Ide => "hd",
+            DeviceNaming::Nvme => "nvme",
+        })
+    }
+}
+
+/// Disk index and partition number of a kernel device name
+///
+/// /dev/sdb3 is disk 1, partition 3; /dev/nvme2n1 is disk 2.
+pub fn parse_disk_device(spec: &str) -> Option<(DeviceNaming, u32, Option<u32>)> {
+    if let Some(rest) = spec.strip_prefix("/dev/nvme") {
+        let (controller, rest) = rest.split_once('n')?;
+        let controller = controller.parse().ok()?;
+        let partition = match rest.split_once('p') {
+            Some(("1", partition)) => Some(partition.parse().ok()?),
+            Some(_) => return None,
+            None if rest == "1" => None,
+            None => return None,
+        };
+        return Some((DeviceNaming::Nvme, controller, partition));
+    }
+
+    let (rest, naming) = DeviceNaming::PREFIXES
+        .iter()
+        .find_map(|(prefix, naming)| spec.strip_prefix(prefix).map(|rest| (rest, *naming)))?;
+    let digits = rest.trim_start_matches(|c: char| c.is_ascii_lowercase());
+    let letters = &rest[..rest.len() - digits.len()];
+    if letters.is_empty() {
+        return None;
+    }
+    // a-z are disks 0-25, aa is 26
+    let mut disk = 0u32;
+    for letter in letters.bytes() {
+        disk = disk
+            .checked_mul(26)?
+            .checked_add(u32::from(letter - b'a') + 1)?;
+    }
+    let partition = match digits {
+        "" => None,
+        digits => Some(digits.parse().ok()?),
+    };
+    Some((naming, disk - 1, partition))
+}
+
+/// Kernel device name of disk `disk` (and `partition`) under `naming`
+pub fn disk_device_name(naming: DeviceNaming, disk: u32, partition: Option<u32>) -> String {
+    if naming == DeviceNaming::Nvme {
+        return match partition {
+            Some(partition) => format!("/dev/nvme{}n1p{}", disk, partition),
+            None => format!("/dev/nvme{}n1", disk),
+        };
+    }
+
+    let mut letters = Vec::new();
+    let mut index = disk + 1;
+    while index > 0 {
+        letters.push(b'a' + ((index - 1) % 26) as u8);
+        index = (index - 1) / 26;
+    }
+    letters.reverse();
+    format!(
+        "/dev/{}{}{}",
+        naming,
+        String::from_utf8_lossy(&letters),
+        partition.map(|p| p.to_string()).unwrap_or_default()
+    )
+}
+
+/// Disk and partition of a /dev/disk/by-* name (`...-part2`)
+fn split_link_partition(spec: &str) -> (&str, Option<u32>) {
+    match spec.rsplit_once("-part") {
+        Some((disk, partition)) => match partition.parse() {
+            Ok(partition) => (disk, Some(partition)),
+            Err(_) => (spec, None),
+        },
+        None => (spec, None),
+    }
+}
+
+/// Renaming of guest device paths for another hypervisor
+///
+/// Kernel names keep their disk index and partition: with
+/// [`DeviceNaming::Virtio`], /dev/sdb2, /dev/xvdb2 and /dev/nvme1n1p2 all
+/// become /dev/vdb2. /dev/disk/by-path names are numbered in sorted order
+/// (bus address, then target), the order the guest enumerated those disks
+/// in. Explicit mappings of devices or whole disks take precedence.
+///
+/// # Examples
+///
+/// ```
+/// use guestkit::guestfs::fstab::{DeviceNaming, DeviceTranslation};
+///
+/// let translation = DeviceTranslation::new(DeviceNaming::Virtio)
+///     .map("/dev/disk/by-id/wwn-0x5000c500a1b2c3d4", "/dev/vdc");
+/// assert_eq!(translation.translate("/dev/nvme0n1p2").as_deref(), Some("/dev/vda2"));
+/// assert_eq!(
+///     translation.translate("/dev/disk/by-id/wwn-0x5000c500a1b2c3d4-part1").as_deref(),
+///     Some("/dev/vdc1")
+/// );
+/// ```
+#[derive(Debug, Clone)]
+pub struct DeviceTranslation {
+    naming: DeviceNaming,
+    table: HashMap<String, String>,
+    by_path: Vec<String>,
+}
+
+impl DeviceTranslation {
+    /// Translation to the disk names of `naming`
+    pub fn new(naming: DeviceNaming) -> Self {
+        DeviceTranslation {
+            naming,
+            table: HashMap::new(),
+            by_path: Vec::new(),
+        }
+    }
+
+    /// Target disk naming
+    pub fn naming(&self) -> DeviceNaming {
+        self.naming
+    }
+
+    /// Translate `from` to `to`
+    ///
+    /// When both are whole disks their partitions are translated too.
+    pub fn map(mut self, from: impl Into<String>, to: impl Into<String>) -> Self {
+        self.table.insert(from.into(), to.into());
+        self
+    }
+
+    /// Number the by-path disks `specs` refer to, with those seen before
+    pub fn learn_by_path<'a>(&mut self, specs: impl IntoIterator<Item = &'a str>) {
+        for spec in specs {
+            if spec.starts_with("/dev/disk/by-path/") {
+                let (disk, _) = split_link_partition(spec);
+                if !self.by_path.iter().any(|known| known == disk) {
+                    self.by_path.push(disk.to_string());
+                }
+            }
+        }
+        self.by_path.sort();
+    }
+
+    /// Device path `spec` has on the target, if it is a device path
+    pub fn translate(&self, spec: &str) -> Option<String> {
+        if let Some(target) = self.table.get(spec) {
+            return Some(target.clone());
+        }
+
+        if let Some((naming, disk, partition)) = parse_disk_device(spec) {
+            let source_disk = disk_device_name(naming, disk, None);
+            return match self.table.get(&source_disk) {
+                Some(target) => Some(Self::with_partition(target, partition)),
+                None => Some(disk_device_name(self.naming, disk, partition)),
+            };
+        }
+
+        if spec.starts_with("/dev/disk/") {
+            let (disk, partition) = split_link_partition(spec);
+            if let Some(target) = self.table.get(disk) {
+                return Some(Self::with_partition(target, partition));
+            }
+            let index = self.by_path.iter().position(|known| known == disk)?;
+            return Some(disk_device_name(self.naming, index as u32, partition));
+        }
+
+        None
+    }
+
+    /// Partition `partition` of the disk `disk`
+    fn with_partition(disk: &str, partition: Option<u32>) -> String {
+        let Some(partition) = partition else {
+            return disk.to_string();
+        };
+        match parse_disk_device(disk) {
+            Some((naming, index, None)) => disk_device_name(naming, index, Some(partition)),
+            _ => format!("{}-part{}", disk, partition),
+        }
+    }
+}
+
 /// Rewrite /etc/fstab with proper UUID/PARTUUID specs
 ///
 /// # Arguments
 /// * `fstab_path` - Path to fstab file
 /// * `inv` - Device inventory
 /// * `btrfs_subvol_map` - Btrfs subvolume mapping (optional)
+/// * `translation` - Renaming of device paths not in the inventory (optional)
 pub fn rewrite_fstab(
     fstab_path: &Path,
     inv: &Inventory,
     btrfs_subvol_map: &BtrfsSubvolMap,
+    translation: Option<&DeviceTranslation>,
 ) -> Result<()> {
     let content = fs::read_to_string(fstab_path)
         .map_err(|e| crate::core::Error::NotFound(format!("Cannot read fstab: {}", e)))?;
 
+    let mut translation = translation.cloned();
+    if let Some(translation) = &mut translation {
+        translation.learn_by_path(
+            content
+                .lines()
+                .filter_map(|line| line.split_whitespace().next()),
+        );
+    }
+    let translate = |spec: &str| translation.as_ref().and_then(|t| t.translate(spec));
+
     let mut output_lines = Vec::new();
 
     for line in content.lines() {
@@ -193,7 +455,13 @@ pub fn rewrite_fstab(
             // Try to find device in inventory
             if let Some(dev_info) = find_by_spec(inv, &entry.spec) {
                 // Determine canonical spec based on fstype
-                let mut new_spec = if entry.fstype == "swap" {
+                let portable = PORTABLE_SPECS
+                    .iter()
+                    .any(|prefix| entry.spec.starts_with(prefix));
+                let mut new_spec = if portable {
+                    // Already names the filesystem or partition, whatever the bus
+                    entry.spec.clone()
+                } else if entry.fstype == "swap" {
                     // Swap always prefers UUID
                     if let Some(uuid) = &dev_info.uuid {
                         format!("UUID={}", uuid)
@@ -215,15 +483,23 @@ pub fn rewrite_fstab(
                     }
 
                     // If not a real partition (LVM/crypt/md), UUID is the portable choice
-                    if !dev_info.is_partition() {
+                    if !portable && !dev_info.is_partition() {
                         if let Some(uuid) = &dev_info.uuid {
                             new_spec = format!("UUID={}", uuid);
                         }
                     }
                 }
 
+                // Without UUIDs the device path has to suit the target bus
+                if new_spec.starts_with("/dev/") {
+                    new_spec = translate(&new_spec).unwrap_or(new_spec);
+                }
+
                 entry.spec = new_spec;
                 output_lines.push(entry.format());
+            } else if let Some(translated) = translate(&entry.spec) {
+                entry.spec = translated;
+                output_lines.push(entry.format());
             } else if entry.needs_rewrite() {
                 // Device not found in inventory but needs rewriting
                 // Keep original but warn
@@ -254,7 +530,12 @@ pub fn rewrite_fstab(
 /// # Arguments
 /// * `crypttab_path` - Path to crypttab file
 /// * `inv` - Device inventory
-pub fn rewrite_crypttab(crypttab_path: &Path, inv: &Inventory) -> Result<()> {
+/// * `translation` - Renaming of device paths not in the inventory (optional)
+pub fn rewrite_crypttab(
+    crypttab_path: &Path,
+    inv: &Inventory,
+    translation: Option<&DeviceTranslation>,
+) -> Result<()> {
     if !crypttab_path.exists() {
         // No crypttab, nothing to do
         return Ok(());
@@ -263,6 +544,15 @@ pub fn rewrite_crypttab(crypttab_path: &Path, inv: &Inventory) -> Result<()> {
     let content = fs::read_to_string(crypttab_path)
         .map_err(|e| crate::core::Error::NotFound(format!("Cannot read crypttab: {}", e)))?;
 
+    let mut translation = translation.cloned();
+    if let Some(translation) = &mut translation {
+        translation.learn_by_path(
+            content
+                .lines()
+                .filter_map(|line| line.split_whitespace().nth(1)),
+        );
+    }
+
     let mut output_lines = Vec::new();
 
     for line in content.lines() {
@@ -277,6 +567,12 @@ pub fn rewrite_crypttab(crypttab_path: &Path, inv: &Inventory) -> Result<()> {
                     entry.device = format!("UUID={}", uuid);
                 }
                 output_lines.push(entry.format());
+            } else if let Some(translated) = translation
+                .as_ref()
+                .and_then(|translation| translation.translate(&entry.device))
+            {
+                entry.device = translated;
+                output_lines.push(entry.format());
             } else {
                 // Keep as-is if not found
                 eprintln!(
@@ -301,6 +597,7 @@ pub fn rewrite_crypttab(crypttab_path: &Path, inv: &Inventory) -> Result<()> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::guestfs::device_inventory::{BlockType, DevInfo};
 
     #[test]
     fn test_fstab_entry_parse() {
@@ -348,6 +645,140 @@ mod tests {
         assert!(!new_opts2.contains("subvol=old"));
     }
 
+    #[test]
+    fn test_parse_disk_device() {
+        assert_eq!(
+            parse_disk_device("/dev/sdb3"),
+            Some((DeviceNaming::Scsi, 1, Some(3)))
+        );
+        assert_eq!(
+            parse_disk_device("/dev/xvda"),
+            Some((DeviceNaming::Xen, 0, None))
+        );
+        assert_eq!(
+            parse_disk_device("/dev/vdaa1"),
+            Some((DeviceNaming::Virtio, 26, Some(1)))
+        );
+        assert_eq!(
+            parse_disk_device("/dev/nvme1n1p2"),
+            Some((DeviceNaming::Nvme, 1, Some(2)))
+        );
+        assert_eq!(
+            parse_disk_device("/dev/nvme0n1"),
+            Some((DeviceNaming::Nvme, 0, None))
+        );
+        assert_eq!(parse_disk_device("/dev/nvme0n2p1"), None);
+        assert_eq!(parse_disk_device("/dev/mapper/vg-root"), None);
+        assert_eq!(parse_disk_device("/dev/sd1"), None);
+
+        assert_eq!(
+            disk_device_name(DeviceNaming::Virtio, 26, Some(1)),
+            "/dev/vdaa1"
+        );
+        assert_eq!(disk_device_name(DeviceNaming::Scsi, 25, None), "/dev/sdz");
+        assert_eq!(
+            disk_device_name(DeviceNaming::Nvme, 2, Some(1)),
+            "/dev/nvme2n1p1"
+        );
+    }
+
+    #[test]
+    fn test_device_translation() {
+        let mut translation = DeviceTranslation::new(DeviceNaming::Virtio)
+            .map("/dev/sdc", "/dev/disk/by-id/virtio-data")
+            .map("/dev/hda1", "/dev/vdd1");
+        translation.learn_by_path([
+            "/dev/disk/by-path/pci-0000:00:10.0-scsi-0:0:1:0-part2",
+            "/dev/disk/by-path/pci-0000:00:10.0-scsi-0:0:0:0-part1",
+        ]);
+
+        assert_eq!(
+            translation.translate("/dev/sda1").as_deref(),
+            Some("/dev/vda1")
+        );
+        assert_eq!(
+            translation.translate("/dev/xvdb").as_deref(),
+            Some("/dev/vdb")
+        );
+        assert_eq!(
+            translation.translate("/dev/nvme0n1p3").as_deref(),
+            Some("/dev/vda3")
+        );
+        assert_eq!(
+            translation.translate("/dev/hda1").as_deref(),
+            Some("/dev/vdd1")
+        );
+        assert_eq!(
+            translation.translate("/dev/sdc2").as_deref(),
+            Some("/dev/disk/by-id/virtio-data-part2")
+        );
+        assert_eq!(
+            translation
+                .translate("/dev/disk/by-path/pci-0000:00:10.0-scsi-0:0:0:0-part1")
+                .as_deref(),
+            Some("/dev/vda1")
+        );
+        assert_eq!(
+            translation
+                .translate("/dev/disk/by-path/pci-0000:00:10.0-scsi-0:0:1:0-part2")
+                .as_deref(),
+            Some("/dev/vdb2")
+        );
+        assert_eq!(translation.translate("UUID=abc-123"), None);
+        assert_eq!(translation.translate("/dev/mapper/vg-root"), None);
+        assert_eq!(translation.translate("tmpfs"), None);
+    }
+
+    #[test]
+    fn test_rewrite_fstab_translation() {
+        let dir = tempfile::tempdir().unwrap();
+        let fstab_path = dir.path().join("fstab");
+        fs::write(
+            &fstab_path,
+            "# root\n\
+             LABEL=root / ext4 defaults 0 1\n\
+             /dev/nvme0n1p2 /home xfs defaults 0 2\n\
+             /dev/sdb1 /srv ext4 defaults 0 2\n\
+             tmpfs /tmp tmpfs defaults 0 0\n",
+        )
+        .unwrap();
+
+        let dev = |dev: &str, uuid: Option<&str>, label: Option<&str>| DevInfo {
+            dev: dev.to_string(),
+            fstype: Some("ext4".to_string()),
+            uuid: uuid.map(str::to_string),
+            label: label.map(str::to_string),
+            partuuid: Some(dev.trim_start_matches("/dev/").to_string()),
+            blk_type: BlockType::Part,
+            luks_uuid: None,
+        };
+        let inventory = Inventory {
+            by_dev: HashMap::from([
+                (
+                    "/dev/sda1".to_string(),
+                    dev("/dev/sda1", Some("1111"), Some("root")),
+                ),
+                ("/dev/sdb1".to_string(), dev("/dev/sdb1", None, None)),
+            ]),
+            by_uuid: HashMap::from([("1111".to_string(), "/dev/sda1".to_string())]),
+            by_partuuid: HashMap::new(),
+            by_label: HashMap::from([("root".to_string(), "/dev/sda1".to_string())]),
+        };
+        let translation = DeviceTranslation::new(DeviceNaming::Virtio);
+
+        rewrite_fstab(&fstab_path, &inventory, &HashMap::new(), Some(&translation)).unwrap();
+        let fstab = fs::read_to_string(&fstab_path).unwrap();
+        let lines: Vec<&str> = fstab.lines().collect();
+        assert_eq!(lines[0], "# root");
+        // Portable specs stay, even where PARTUUID would be preferred
+        assert!(lines[1].starts_with("LABEL=root\t/\t"), "{}", lines[1]);
+        // Not in the inventory: renamed for virtio
+        assert!(lines[2].starts_with("/dev/vda2\t/home\t"), "{}", lines[2]);
+        // In the inventory, but only PARTUUID
+        assert!(lines[3].starts_with("PARTUUID=sdb1\t"), "{}", lines[3]);
+        assert_eq!(lines[4], "tmpfs /tmp tmpfs defaults 0 0");
+    }
+
     #[test]
     fn test_crypttab_entry_parse() {
         let line = "cryptroot /dev/sda2 none luks";
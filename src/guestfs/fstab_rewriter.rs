@@ -3,11 +3,36 @@
 
 use crate::core::Result;
 use crate::guestfs::device_inventory::{build_inventory, Inventory};
-use crate::guestfs::fstab::{rewrite_crypttab, rewrite_fstab, BtrfsSubvolMap};
+use crate::guestfs::fstab::{rewrite_crypttab, rewrite_fstab, BtrfsSubvolMap, DeviceTranslation};
 use crate::guestfs::Guestfs;
 use std::collections::HashMap;
 
 impl Guestfs {
+    /// Rename device paths for another hypervisor when rewriting fstab and crypttab
+    ///
+    /// Entries whose device the inventory does not know (disks that are not
+    /// attached, names of another bus) keep their device path; with a
+    /// translation they get the target's name for it instead.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use guestkit::guestfs::fstab::{DeviceNaming, DeviceTranslation};
+    /// use guestkit::guestfs::Guestfs;
+    ///
+    /// let mut g = Guestfs::new()?;
+    /// g.set_device_translation(Some(DeviceTranslation::new(DeviceNaming::Virtio)));
+    /// # Ok::<(), Box<dyn std::error::Error>>(())
+    /// ```
+    pub fn set_device_translation(&mut self, translation: Option<DeviceTranslation>) {
+        self.device_translation = translation;
+    }
+
+    /// Get the device translation used when rewriting
+    pub fn get_device_translation(&self) -> Option<&DeviceTranslation> {
+        self.device_translation.as_ref()
+    }
+
     /// Build device inventory from all available block devices
     ///
     /// This queries all partitions, LVM volumes, and other block devices
@@ -54,7 +79,9 @@ impl Guestfs {
     /// - UUID= for everything else (LVM, mdraid, non-boot partitions)
     /// - Proper subvol= options for btrfs
     ///
-    /// Never keeps: /dev/sdX, /dev/vdX, /dev/nbdX, /dev/disk/by-path/*
+    /// Never keeps: /dev/sdX, /dev/vdX, /dev/xvdX, /dev/nvmeXnYpZ, /dev/nbdX,
+    /// /dev/disk/by-path/* (see [`Guestfs::set_device_translation`] for
+    /// devices without UUIDs)
     ///
     /// # Arguments
     ///
@@ -111,7 +138,12 @@ impl Guestfs {
         let btrfs_map: BtrfsSubvolMap = btrfs_subvols.cloned().unwrap_or_default();
 
         // Rewrite fstab
-        rewrite_fstab(&fstab_path, &inventory, &btrfs_map)?;
+        rewrite_fstab(
+            &fstab_path,
+            &inventory,
+            &btrfs_map,
+            self.device_translation.as_ref(),
+        )?;
 
         if self.verbose {
             eprintln!("guestfs: rewrote /etc/fstab with proper UUID/PARTUUID specs");
@@ -170,7 +202,7 @@ impl Guestfs {
         let crypttab_path = mount_root.join("etc/crypttab");
 
         // Rewrite crypttab (if it exists)
-        rewrite_crypttab(&crypttab_path, &inventory)?;
+        rewrite_crypttab(&crypttab_path, &inventory, self.device_translation.as_ref())?;
 
         if self.verbose {
             eprintln!("guestfs: rewrote /etc/crypttab with proper LUKS UUIDs");
@@ -219,6 +219,54 @@ impl Guestfs {
             "Failed to update GRUB configuration".to_string(),
         ))
     }
+
+    /// List the grub.cfg files the guest boots from
+    ///
+    /// EFI grub.cfg stubs that only load the one in /boot (`configfile`)
+    /// are left out.
+    pub fn grub_config_paths(&mut self) -> Vec<String> {
+        let mut configs = Vec::new();
+        for config in ["/boot/grub2/grub.cfg", "/boot/grub/grub.cfg"] {
+            if self.is_file(config).unwrap_or(false) {
+                configs.push(config.to_string());
+            }
+        }
+        for vendor in self.ls("/boot/efi/EFI").unwrap_or_default() {
+            let config = format!("/boot/efi/EFI/{}/grub.cfg", vendor);
+            let Ok(content) = self.cat(&config) else {
+                continue;
+            };
+            let stub = content
+                .lines()
+                .any(|line| line.trim_start().starts_with("configfile"));
+            if !stub {
+                configs.push(config);
+            }
+        }
+        configs
+    }
+
+    /// Regenerate a grub.cfg with the guest's grub2-mkconfig
+    ///
+    /// os-prober is disabled, it would add menu entries for host disks.
+    pub fn grub_mkconfig(&mut self, config: &str) -> Result<()> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: grub_mkconfig {}", config);
+        }
+
+        let program = ["grub2-mkconfig", "grub-mkconfig"]
+            .into_iter()
+            .find(|program| self.guest_program(program))
+            .ok_or_else(|| Error::NotFound("grub2-mkconfig not found in the guest".to_string()))?;
+
+        self.command_with_devices(
+            &[("GRUB_DISABLE_OS_PROBER", "true")],
+            &[program, "-o", config],
+        )?;
+        Ok(())
+    }
 }
 
 #[cfg(test)]
@@ -1,10 +1,15 @@
 // SPDX-License-Identifier: LGPL-3.0-or-later
 //! Main GuestFS handle implementation
 
+use super::bitlocker::BitlockerKey;
+use super::fstab::DeviceTranslation;
+use super::luks::LuksKey;
+use super::session_trace::Replay;
 use crate::core::{Error, Result};
 use crate::disk::{DiskReader, LoopDevice, NbdDevice, PartitionTable};
 use std::collections::HashMap;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
 use std::time::Duration;
 
 /// GuestFS handle state
@@ -66,6 +71,7 @@ pub struct Guestfs {
     pub(crate) loop_device: Option<LoopDevice>,
     pub(crate) mounted: HashMap<String, String>, // device -> mountpoint
     pub(crate) mount_root: Option<PathBuf>,      // Temporary mount directory
+    pub(crate) live_carriers: HashMap<String, Vec<PathBuf>>, // liveimg mountable -> carrier mounts
     pub(crate) lazy_unmount_used: bool,          // Track if lazy unmount was needed
     pub(crate) activated_vgs: Vec<String>,       // Track activated LVM volume groups for cleanup
     pub(crate) identifier: Option<String>,
@@ -74,6 +80,53 @@ pub struct Guestfs {
     pub(crate) utf8_policy: Utf8Policy,
     pub(crate) resource_limits: ResourceLimits,
     pub(crate) windows_version_cache: HashMap<String, (String, String, String)>, // Cache for Windows registry data (root -> (product, version, edition))
+    pub(crate) bitlocker_keys: Vec<(Option<String>, BitlockerKey)>, // Keys to try at launch (device filter, key)
+    pub(crate) bitlocker_volumes: HashMap<String, String>, // Unlocked BitLocker volumes (device -> mapname)
+    pub(crate) luks_keys: Vec<(Option<String>, LuksKey)>, // Keys to try at launch (device or UUID filter, key)
+    pub(crate) luks_volumes: HashMap<String, String>, // Unlocked LUKS volumes (device -> mapname)
+    pub(crate) extra_drives: Vec<ExtraDrive>,         // Block devices of drives after the first
+    pub(crate) md_arrays: Vec<String>, // md arrays assembled at launch (/dev/md/name)
+    pub(crate) os_root: Option<String>, // Root inspect_os puts first (device or 1-based number)
+    pub(crate) fstab_options: Option<String>, // Options mount plans use instead of the fstab's
+    pub(crate) device_translation: Option<DeviceTranslation>, // Device renaming for fstab rewriting
+    pub(crate) replay: Option<Arc<Replay>>, // Session trace answering calls instead of the disks
+    pub(crate) enable_writes: bool, // Allow the pure Rust ext4 writes (ext4_* operations)
+}
+
+/// Block device of a drive after the first
+///
+/// Only md and LVM see these drives, through their member devices.
+pub(crate) enum ExtraDrive {
+    Loop(LoopDevice),
+    Nbd(NbdDevice),
+}
+
+impl ExtraDrive {
+    fn attach(drive: &DriveConfig) -> Result<Self> {
+        if LoopDevice::is_format_supported(&drive.path) {
+            let mut loop_dev = LoopDevice::new()?;
+            loop_dev.connect(&drive.path, drive.readonly)?;
+            Ok(ExtraDrive::Loop(loop_dev))
+        } else {
+            let mut nbd = NbdDevice::new()?;
+            nbd.connect(&drive.path, drive.readonly)?;
+            Ok(ExtraDrive::Nbd(nbd))
+        }
+    }
+
+    pub(crate) fn device_path(&self) -> Option<&Path> {
+        match self {
+            ExtraDrive::Loop(loop_dev) => loop_dev.device_path(),
+            ExtraDrive::Nbd(nbd) => Some(nbd.device_path()),
+        }
+    }
+
+    fn disconnect(&mut self) -> Result<()> {
+        match self {
+            ExtraDrive::Loop(loop_dev) => loop_dev.disconnect(),
+            ExtraDrive::Nbd(nbd) => nbd.disconnect(),
+        }
+    }
 }
 
 /// Drive configuration
@@ -108,6 +161,7 @@ impl Guestfs {
             loop_device: None,
             mounted: HashMap::new(),
             mount_root: None,
+            live_carriers: HashMap::new(),
             lazy_unmount_used: false,
             activated_vgs: Vec::new(),
             identifier: None,
@@ -116,6 +170,17 @@ impl Guestfs {
             utf8_policy: Utf8Policy::Lossy,
             resource_limits: ResourceLimits::default(),
             windows_version_cache: HashMap::new(),
+            bitlocker_keys: Vec::new(),
+            bitlocker_volumes: HashMap::new(),
+            luks_keys: super::luks::default_keys(),
+            luks_volumes: HashMap::new(),
+            extra_drives: Vec::new(),
+            md_arrays: Vec::new(),
+            os_root: None,
+            fstab_options: super::mount_plan::default_fstab_options(),
+            device_translation: None,
+            replay: super::session_trace::current_replay(),
+            enable_writes: false,
         })
     }
 
@@ -173,10 +238,17 @@ impl Guestfs {
             return Err(Error::InvalidState("No drives added".to_string()));
         }
 
+        // Replayed sessions attach nothing; the trace answers the calls
+        if self.replay.is_some() {
+            self.state = GuestfsState::Ready;
+            return Ok(());
+        }
+
         // Transition to Launching state
         self.state = GuestfsState::Launching;
 
-        // Open the first drive (for now, multi-drive support TODO)
+        // The first drive is the disk inspected; further drives only
+        // contribute md and LVM members
         let drive = &self.drives[0];
 
         // Attempt to launch - if any error occurs, move to Error state
@@ -237,6 +309,13 @@ impl Guestfs {
                 self.nbd_device = Some(nbd);
             }
 
+            for extra in &self.drives[1..] {
+                if self.trace {
+                    eprintln!("guestfs: attaching {}", extra.path.display());
+                }
+                self.extra_drives.push(ExtraDrive::attach(extra)?);
+            }
+
             Ok(())
         })();
 
@@ -244,6 +323,28 @@ impl Guestfs {
             Ok(_) => {
                 self.state = GuestfsState::Ready;
 
+                // Assemble md arrays before unlocking, they may hold LUKS
+                // or LVM. A missing mdadm only matters for RAID guests.
+                if let Err(e) = self.md_assemble_all() {
+                    eprintln!("Warning: md array assembly failed: {}", e);
+                }
+
+                // Unlock BitLocker volumes that keys were registered for
+                if !self.bitlocker_keys.is_empty() {
+                    if let Err(e) = self.unlock_bitlocker_volumes() {
+                        self.state = GuestfsState::Error(e.to_string());
+                        return Err(e);
+                    }
+                }
+
+                // Unlock LUKS volumes that keys were registered for
+                if !self.luks_keys.is_empty() {
+                    if let Err(e) = self.unlock_luks_volumes() {
+                        self.state = GuestfsState::Error(e.to_string());
+                        return Err(e);
+                    }
+                }
+
                 if self.trace {
                     eprintln!("guestfs: launched with {} drive(s)", self.drives.len());
                 }
@@ -263,6 +364,10 @@ impl Guestfs {
         if self.state == GuestfsState::Closed {
             return Ok(());
         }
+        if self.replay.is_some() {
+            self.state = GuestfsState::Closed;
+            return Ok(());
+        }
 
         if self.trace {
             eprintln!("guestfs: shutdown - starting cleanup");
@@ -372,6 +477,43 @@ impl Guestfs {
             self.activated_vgs.clear();
         }
 
+        // Step 1.6: Close BitLocker mappings
+        // After unmount, before the backing loop/NBD device goes away
+        if !self.bitlocker_volumes.is_empty() {
+            if self.trace {
+                eprintln!("guestfs: closing {} BitLocker volume(s)", self.bitlocker_volumes.len());
+            }
+            self.close_bitlocker_volumes();
+        }
+
+        // Step 1.7: Close LUKS mappings opened at launch
+        if !self.luks_volumes.is_empty() {
+            if self.trace {
+                eprintln!("guestfs: closing {} LUKS volume(s)", self.luks_volumes.len());
+            }
+            self.close_luks_volumes();
+        }
+
+        // Step 1.8: Stop md arrays, releasing their member devices
+        if !self.md_arrays.is_empty() {
+            if self.trace {
+                eprintln!("guestfs: stopping {} md array(s)", self.md_arrays.len());
+            }
+            self.md_stop_all();
+        }
+
+        // Step 1.9: Disconnect the block devices of further drives
+        // Like the main NBD device below, they stay if lazy unmount was used
+        for mut extra in self.extra_drives.drain(..) {
+            if self.lazy_unmount_used {
+                std::mem::forget(extra);
+                continue;
+            }
+            if let Err(e) = extra.disconnect() {
+                eprintln!("Warning: drive disconnect failed: {}", e);
+            }
+        }
+
         // Step 2: Disconnect loop device
         if let Some(mut loop_dev) = self.loop_device.take() {
             if self.trace {
@@ -565,6 +707,17 @@ impl Guestfs {
         self.trace
     }
 
+    /// Allow the pure Rust ext4 writes, which change the image without
+    /// launching or mounting it
+    pub fn set_enable_writes(&mut self, enable: bool) {
+        self.enable_writes = enable;
+    }
+
+    /// Get whether pure Rust ext4 writes are allowed
+    pub fn get_enable_writes(&self) -> bool {
+        self.enable_writes
+    }
+
     /// Set debug mode
     pub fn set_debug(&mut self, debug: bool) {
         self.debug = debug;
@@ -596,6 +749,20 @@ impl Guestfs {
     }
 
     /// Get NBD device reference safely (internal)
+    /// Host block devices of all drives, whole disks only
+    pub(crate) fn drive_devices(&self) -> Vec<PathBuf> {
+        let main = self
+            .loop_device
+            .as_ref()
+            .and_then(|loop_dev| loop_dev.device_path())
+            .or_else(|| self.nbd_device.as_ref().map(|nbd| nbd.device_path()));
+        let extra = self.extra_drives.iter().filter_map(ExtraDrive::device_path);
+        main.into_iter()
+            .chain(extra)
+            .map(Path::to_path_buf)
+            .collect()
+    }
+
     pub(crate) fn nbd_device(&self) -> Result<&NbdDevice> {
         self.nbd_device.as_ref().ok_or_else(|| {
             Error::InvalidState(
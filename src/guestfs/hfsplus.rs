@@ -0,0 +1,71 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! HFS+ operations for disk image manipulation
+//!
+//! Reads the HFS+ volumes of older macOS guests with the pure Rust reader;
+//! nothing is launched or mounted.
+
+use crate::core::Result;
+use crate::disk::{HfsPlus, HfsPlusDirEntry};
+use crate::guestfs::Guestfs;
+use std::path::Path;
+
+impl Guestfs {
+    /// Open the HFS+ volume on `device` of the first drive with the pure
+    /// Rust reader
+    pub fn hfsplus_open(&self, device: &str) -> Result<HfsPlus> {
+        let (reader, offset) = self.open_partition(device)?;
+        HfsPlus::from_reader(reader, offset)
+    }
+
+    /// List directory `path` of the HFS+ volume on `device`
+    ///
+    pub fn hfsplus_ls(&mut self, device: &str, path: &str) -> Result<Vec<HfsPlusDirEntry>> {
+        if self.verbose {
+            eprintln!("guestfs: hfsplus_ls {} {}", device, path);
+        }
+
+        self.hfsplus_open(device)?.list_dir(path)
+    }
+
+    /// Read a file of the HFS+ volume on `device`, decompressed if macOS
+    /// stored it compressed
+    ///
+    pub fn hfsplus_read_file(&mut self, device: &str, path: &str) -> Result<Vec<u8>> {
+        if self.verbose {
+            eprintln!("guestfs: hfsplus_read_file {} {}", device, path);
+        }
+
+        self.hfsplus_open(device)?.read_file(path)
+    }
+
+    /// Copy a file of the HFS+ volume on `device` to `filename` on the host
+    ///
+    pub fn hfsplus_download<P: AsRef<Path>>(
+        &mut self,
+        device: &str,
+        path: &str,
+        filename: P,
+    ) -> Result<()> {
+        let content = self.hfsplus_read_file(device, path)?;
+        std::fs::write(filename, content)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::core::Error;
+
+    #[test]
+    fn test_hfsplus_read_file_needs_a_drive() {
+        let mut g = Guestfs::new().unwrap();
+        assert!(matches!(
+            g.hfsplus_read_file(
+                "/dev/sda2",
+                "/System/Library/CoreServices/SystemVersion.plist"
+            ),
+            Err(Error::InvalidState(_))
+        ));
+    }
+}
@@ -0,0 +1,146 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Boot-relevant properties of an image: OS, firmware and the disk bus the
+//! guest has drivers for
+//!
+//! Publishing uses them to tag images; `generate` to write a domain for one.
+
+use crate::core::Result;
+use crate::guestfs::Guestfs;
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+/// EFI System Partition type GUID
+const ESP_GUID: &str = "C12A7328-F81F-11D2-BA4B-00A0C93EC93B";
+
+/// Disk bus the guest has drivers to boot from
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum DiskBus {
+    Virtio,
+    VirtioScsi,
+    Sata,
+    Ide,
+}
+
+impl fmt::Display for DiskBus {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            DiskBus::Virtio => "virtio",
+            DiskBus::VirtioScsi => "virtio-scsi",
+            DiskBus::Sata => "sata",
+            DiskBus::Ide => "ide",
+        })
+    }
+}
+
+/// Properties of an image, derived from inspection
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImageProperties {
+    /// "linux", "windows", ...
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub os_type: Option<String>,
+    /// Distribution as reported by inspection ("fedora", "ubuntu", ...)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub distro: Option<String>,
+    /// "major.minor"
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub product_name: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub architecture: Option<String>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub disk_bus: Option<DiskBus>,
+    /// Boots through UEFI (has an EFI System Partition)
+    #[serde(default)]
+    pub uefi: bool,
+}
+
+impl ImageProperties {
+    /// Inspect `image` read-only
+    ///
+    /// Images without a recognizable OS give empty properties rather than
+    /// an error, so they can still be published.
+    pub fn detect(image: &Path) -> Result<Self> {
+        let mut g = Guestfs::new()?;
+        g.add_drive_ro(image)?;
+        g.launch()?;
+
+        let properties = Self::inspect(&mut g);
+        let _ = g.shutdown();
+        properties
+    }
+
+    fn inspect(g: &mut Guestfs) -> Result<Self> {
+        let mut properties = Self {
+            uefi: has_esp(g),
+            ..Self::default()
+        };
+
+        let Some(root) = g.inspect_os()?.into_iter().next() else {
+            return Ok(properties);
+        };
+        properties.os_type = g.inspect_get_type(&root).ok();
+        properties.distro = g.inspect_get_distro(&root).ok();
+        properties.product_name = g.inspect_get_product_name(&root).ok();
+        properties.architecture = g.inspect_get_arch(&root).ok();
+        if let Ok(major) = g.inspect_get_major_version(&root) {
+            let minor = g.inspect_get_minor_version(&root).unwrap_or(0);
+            properties.version = Some(format!("{}.{}", major, minor));
+        }
+
+        properties.disk_bus = match properties.os_type.as_deref() {
+            // virtio_blk ships with every mainstream kernel
+            Some("linux") => Some(DiskBus::Virtio),
+            Some("windows") => Some(windows_disk_bus(g, &root)),
+            _ => None,
+        };
+        Ok(properties)
+    }
+}
+
+/// Whether the image has an EFI System Partition
+fn has_esp(g: &mut Guestfs) -> bool {
+    let Ok(partitions) = g.list_partitions() else {
+        return false;
+    };
+    partitions.iter().any(|partition| {
+        let (Ok(device), Ok(number)) = (g.part_to_dev(partition), g.part_to_partnum(partition))
+        else {
+            return false;
+        };
+        g.part_get_gpt_type(&device, number)
+            .is_ok_and(|guid| guid.to_uppercase().contains(ESP_GUID))
+    })
+}
+
+/// Bus a Windows guest can boot from, by the storage drivers it has
+fn windows_disk_bus(g: &mut Guestfs, root: &str) -> DiskBus {
+    let Ok(mountpoints) = g.inspect_get_mountpoints(root) else {
+        return DiskBus::Sata;
+    };
+    let Some(device) = mountpoints.get("/") else {
+        return DiskBus::Sata;
+    };
+    if g.mount_ro(device, "/").is_err() {
+        return DiskBus::Sata;
+    }
+
+    let mut has_driver = |name: &str| {
+        g.case_sensitive_path(&format!("/Windows/System32/drivers/{}", name))
+            .and_then(|path| g.is_file(&path))
+            .unwrap_or(false)
+    };
+    let bus = if has_driver("vioscsi.sys") {
+        DiskBus::VirtioScsi
+    } else if has_driver("viostor.sys") {
+        DiskBus::Virtio
+    } else {
+        // Inbox AHCI driver
+        DiskBus::Sata
+    };
+
+    let _ = g.umount_all();
+    bus
+}
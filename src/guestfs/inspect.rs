@@ -17,9 +17,46 @@
 //! If your actual API names differ slightly, adjust accordingly.
 
 use crate::core::{Error, Result};
-use crate::disk::FileSystem;
+use crate::disk::{FileSystem, FileSystemType, Partition};
+use crate::guestfs::btrfs::{btrfsvol_mountable, BtrfsSubvolume, BTRFS_TOP_LEVEL_ID};
+use crate::guestfs::fstab::FstabEntry;
+use crate::guestfs::iso::LIVEIMG_PREFIX;
 use crate::guestfs::Guestfs;
-use std::collections::HashMap;
+use std::collections::BTreeMap;
+
+/// Subvolume names distributions give the btrfs root (`@` on Ubuntu/Debian)
+const ROOT_SUBVOLUMES: [&str; 4] = ["@", "@root", "@rootfs", "root"];
+
+/// Move the root `selector` picks to the front of `roots`
+///
+/// The selector is a root as `inspect_os` returns it or its position,
+/// counting from 1.
+pub fn select_os_root(roots: &mut Vec<String>, selector: &str) -> Result<()> {
+    let index = match selector.parse::<usize>() {
+        Ok(number) if (1..=roots.len()).contains(&number) => Some(number - 1),
+        Ok(_) => None,
+        Err(_) => roots.iter().position(|root| root == selector),
+    };
+    let Some(index) = index else {
+        return Err(Error::NotFound(format!(
+            "OS root '{}' not found; detected roots: {}",
+            selector,
+            if roots.is_empty() {
+                "none".to_string()
+            } else {
+                roots
+                    .iter()
+                    .enumerate()
+                    .map(|(i, root)| format!("{} ({})", root, i + 1))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            }
+        )));
+    };
+    let root = roots.remove(index);
+    roots.insert(0, root);
+    Ok(())
+}
 
 /// OS inspection information
 #[derive(Debug, Clone)]
@@ -33,7 +70,7 @@ pub struct InspectedOS {
     pub arch: String,
     pub hostname: String,
     pub package_format: String,
-    pub mountpoints: HashMap<String, String>,
+    pub mountpoints: BTreeMap<String, String>,
 }
 
 impl Guestfs {
@@ -91,7 +128,7 @@ impl Guestfs {
     ///
     /// Returns a list of *validated* root devices where operating systems were found.
     /// Validation is done by mounting candidates RO and checking for OS root markers.
-    pub fn inspect_os(&mut self) -> Result<Vec<String>> {
+    pub(crate) fn inspect_os_untraced(&mut self) -> Result<Vec<String>> {
         self.ensure_ready()?;
 
         let mut roots = crate::core::mem_optimize::vec_for_partitions();
@@ -128,19 +165,74 @@ impl Guestfs {
 
             if let Ok(fs) = FileSystem::detect(reader, p) {
                 match fs.fs_type() {
-                    crate::disk::FileSystemType::Ext
-                    | crate::disk::FileSystemType::Xfs
-                    | crate::disk::FileSystemType::Btrfs
-                    | crate::disk::FileSystemType::Ntfs => {
+                    FileSystemType::Ext => {
                         if self.validate_root_partition(&dev)? {
                             roots.push(dev);
+                        } else {
+                            // Image based OSes (Kairos) keep their roots as
+                            // images on ext4 state and recovery partitions
+                            roots.extend(self.find_live_roots(&dev)?);
+                        }
+                    }
+                    FileSystemType::Xfs
+                    | FileSystemType::Ntfs
+                    | FileSystemType::Squashfs
+                    | FileSystemType::Erofs
+                        if self.validate_root_partition(&dev)? =>
+                    {
+                        roots.push(dev);
+                    }
+                    FileSystemType::Btrfs => {
+                        if let Some(root) = self.find_btrfs_root(&dev)? {
+                            roots.push(root);
                         }
                     }
+                    FileSystemType::Iso9660 => {
+                        roots.extend(self.find_live_roots(&dev)?);
+                    }
                     _ => {}
                 }
             }
         }
 
+        // 1a) Live media without a partition table (plain ISO9660 images)
+        if partitions.is_empty() {
+            let whole_disk = Partition {
+                number: 0,
+                start_lba: 0,
+                size_sectors: 0,
+                type_id: 0,
+                bootable: false,
+                type_guid: None,
+            };
+            let reader = self
+                .reader
+                .as_mut()
+                .ok_or_else(|| Error::InvalidState("Reader not initialized".to_string()))?;
+            if FileSystem::detect(reader, &whole_disk)
+                .is_ok_and(|fs| *fs.fs_type() == FileSystemType::Iso9660)
+            {
+                roots.extend(self.find_live_roots(&disk_dev)?);
+            }
+        }
+
+        // 1b) md arrays assembled from the drives
+        for md in self.md_arrays.clone() {
+            match self.vfs_type(&md)?.as_str() {
+                "ext2" | "ext3" | "ext4" | "xfs" | "ntfs"
+                    if self.validate_root_partition(&md)? =>
+                {
+                    roots.push(md);
+                }
+                "btrfs" => {
+                    if let Some(root) = self.find_btrfs_root(&md)? {
+                        roots.push(root);
+                    }
+                }
+                _ => {}
+            }
+        }
+
         // 2) LVM logical volume candidates (validated)
         if let Ok(lvs) = self.lvs() {
             // Prefer typical root LV names first (stable priority).
@@ -177,6 +269,36 @@ impl Guestfs {
             }
         }
 
+        if let Some(selector) = &self.os_root {
+            select_os_root(&mut roots, selector)?;
+        }
+
+        Ok(roots)
+    }
+
+    /// Choose the OS root that `inspect_os` returns first
+    ///
+    /// On multi-boot images and images with rescue partitions `inspect_os`
+    /// finds several roots, and callers working on one OS use the first.
+    /// `selector` is a root device as `inspect_os` returns it, or its
+    /// position counting from 1. `inspect_os` fails if nothing matches.
+    pub fn set_os_root(&mut self, selector: Option<&str>) {
+        self.os_root = selector.map(str::to_string);
+    }
+
+    /// Get the OS root selector
+    pub fn get_os_root(&self) -> Option<&str> {
+        self.os_root.as_deref()
+    }
+
+    /// Roots for commands that process every OS
+    ///
+    /// All of them, or only the one chosen with [`Guestfs::set_os_root`].
+    pub fn inspect_os_selected(&mut self) -> Result<Vec<String>> {
+        let mut roots = self.inspect_os()?;
+        if self.os_root.is_some() {
+            roots.truncate(1);
+        }
         Ok(roots)
     }
 
@@ -206,8 +328,47 @@ impl Guestfs {
         Ok(is_linux || is_windows)
     }
 
+    /// Root of a btrfs filesystem.
+    ///
+    /// The default subvolume if it holds an OS, otherwise a subvolume of the
+    /// top level that does: with `@` layouts the default subvolume is the top
+    /// level and fstab mounts `/` with `subvol=@`.
+    fn find_btrfs_root(&mut self, dev: &str) -> Result<Option<String>> {
+        if self.validate_root_partition(dev)? {
+            return Ok(Some(dev.to_string()));
+        }
+
+        let top_level = btrfsvol_mountable(dev, "");
+        self.mount_ro(&top_level, "/")?;
+
+        let subvolumes = self.btrfs_subvolumes("/").unwrap_or_default();
+        let root = root_subvolume_candidates(&subvolumes)
+            .into_iter()
+            .find(|path| {
+                let mut has =
+                    |file: &str| self.exists(&format!("/{}/{}", path, file)).unwrap_or(false);
+                (has("etc/os-release") || has("usr/lib/os-release"))
+                    && (has("bin/sh") || has("usr/bin/env"))
+            });
+
+        let _ = self.umount(&top_level);
+        Ok(root.map(|path| btrfsvol_mountable(dev, &path)))
+    }
+
+    /// Roots of live media on `dev`: those of its root images holding an OS
+    fn find_live_roots(&mut self, dev: &str) -> Result<Vec<String>> {
+        let mut roots = Vec::new();
+        for image in self.live_root_images(dev)? {
+            // Images that don't mount (unsupported filesystems) hold no root
+            if self.validate_root_partition(&image).unwrap_or(false) {
+                roots.push(image);
+            }
+        }
+        Ok(roots)
+    }
+
     /// Get the type of operating system (linux/windows/unknown).
-    pub fn inspect_get_type(&mut self, root: &str) -> Result<String> {
+    pub(crate) fn inspect_get_type_untraced(&mut self, root: &str) -> Result<String> {
         self.ensure_ready()?;
 
         // If root is an LV, parse_device_name may fail; use marker detection instead.
@@ -234,7 +395,7 @@ impl Guestfs {
     }
 
     /// Get the distribution name (Linux ID from os-release; Windows edition if available).
-    pub fn inspect_get_distro(&mut self, root: &str) -> Result<String> {
+    pub(crate) fn inspect_get_distro_untraced(&mut self, root: &str) -> Result<String> {
         self.ensure_ready()?;
 
         let os_type = self.inspect_get_type(root)?;
@@ -457,7 +618,7 @@ impl Guestfs {
     }
 
     /// Get the product name.
-    pub fn inspect_get_product_name(&mut self, root: &str) -> Result<String> {
+    pub(crate) fn inspect_get_product_name_untraced(&mut self, root: &str) -> Result<String> {
         let os_type = self.inspect_get_type(root)?;
 
         if os_type == "windows" {
@@ -501,7 +662,7 @@ impl Guestfs {
     ///
     /// Without reading ELF/PE headers (binary-safe read), this uses conservative hints.
     /// If uncertain, returns "unknown".
-    pub fn inspect_get_arch(&mut self, root: &str) -> Result<String> {
+    pub(crate) fn inspect_get_arch_untraced(&mut self, root: &str) -> Result<String> {
         self.ensure_ready()?;
 
         let os_type = self.inspect_get_type(root)?;
@@ -557,7 +718,7 @@ impl Guestfs {
     }
 
     /// Get the major version number.
-    pub fn inspect_get_major_version(&mut self, root: &str) -> Result<i32> {
+    pub(crate) fn inspect_get_major_version_untraced(&mut self, root: &str) -> Result<i32> {
         self.ensure_ready()?;
 
         let os_type = self.inspect_get_type(root)?;
@@ -581,7 +742,7 @@ impl Guestfs {
     }
 
     /// Get the minor version number.
-    pub fn inspect_get_minor_version(&mut self, root: &str) -> Result<i32> {
+    pub(crate) fn inspect_get_minor_version_untraced(&mut self, root: &str) -> Result<i32> {
         self.ensure_ready()?;
 
         let os_type = self.inspect_get_type(root)?;
@@ -606,7 +767,9 @@ impl Guestfs {
     }
 
     /// Get the hostname (read-only; never mounts RW).
-    pub fn inspect_get_hostname(&mut self, root: &str) -> Result<String> {
+    pub(crate) fn inspect_get_hostname_untraced(&mut self, root: &str) -> Result<String> {
+        use crate::guestfs::windows_registry::get_computer_name;
+
         self.ensure_ready()?;
 
         let was_mounted = self.mounted.contains_key(root);
@@ -615,7 +778,19 @@ impl Guestfs {
             self.mount_ro(root, "/")?;
         }
 
-        let hostname = {
+        // Windows computer name from the SYSTEM hive
+        let windows_hostname = if looks_like_windows_root(self) {
+            self.inspect_get_windows_system_hive(root)
+                .and_then(|hive| self.resolve_guest_path(&hive))
+                .and_then(|path| get_computer_name(&path))
+                .ok()
+        } else {
+            None
+        };
+
+        let hostname = if let Some(name) = windows_hostname {
+            name
+        } else {
             // Linux hostname
             if let Ok(content) = self.cat("/etc/hostname") {
                 let t = content.trim();
@@ -665,7 +840,7 @@ impl Guestfs {
     /// Get the package format (rpm/deb/apk/pacman/unknown).
     ///
     /// Uses os-release `ID` + `ID_LIKE` for broader coverage.
-    pub fn inspect_get_package_format(&mut self, root: &str) -> Result<String> {
+    pub(crate) fn inspect_get_package_format_untraced(&mut self, root: &str) -> Result<String> {
         let os_type = self.inspect_get_type(root)?;
         if os_type == "windows" {
             return Ok("msi".to_string()); // not strictly “package format”, but useful
@@ -689,6 +864,9 @@ impl Guestfs {
             if has("arch") || has("archlinux") || has("manjaro") {
                 return Ok("pacman".to_string());
             }
+            if has("gentoo") {
+                return Ok("ebuild".to_string());
+            }
             if has("rhel")
                 || has("fedora")
                 || has("centos")
@@ -714,37 +892,147 @@ impl Guestfs {
             "ubuntu" | "debian" => Ok("deb".to_string()),
             "arch" => Ok("pacman".to_string()),
             "alpine" => Ok("apk".to_string()),
+            "gentoo" => Ok("ebuild".to_string()),
             _ => Ok("unknown".to_string()),
         }
     }
 
-    /// Get mountpoints for the root device.
+    /// Get mountpoints for the root device from its `/etc/fstab`.
     ///
-    /// v0: root always at `/`.
-    /// TODO: parse /etc/fstab, resolve UUID/LABEL, handle btrfs subvol=.
-    pub fn inspect_get_mountpoints(&mut self, root: &str) -> Result<HashMap<String, String>> {
+    /// Devices are matched by `UUID=`, `LABEL=` or name (the guest's
+    /// `/dev/vda2` is `/dev/sda2` here). Btrfs entries with `subvol=` map to
+    /// `btrfsvol:` mountables of that subvolume. `/` is always `root`; entries
+    /// matching no device (network filesystems, swap, `PARTUUID=`) are left out.
+    ///
+    /// Mountpoints are sorted, so iterating mounts each one after its parent.
+    pub(crate) fn inspect_get_mountpoints_untraced(
+        &mut self,
+        root: &str,
+    ) -> Result<BTreeMap<String, String>> {
         self.ensure_ready()?;
 
-        let mut mountpoints = HashMap::new();
+        let mut mountpoints = BTreeMap::new();
         mountpoints.insert("/".to_string(), root.to_string());
+
+        let Some((entries, devices)) = self.fstab_entries(root) else {
+            return Ok(mountpoints);
+        };
+
+        for entry in entries {
+            if !entry.mountpoint.starts_with('/')
+                || entry.mountpoint == "/"
+                || entry.fstype == "swap"
+            {
+                continue;
+            }
+            let Some(device) = self.resolve_fstab_spec(&entry.spec, &devices) else {
+                continue;
+            };
+            let mountable = entry.mountable(device);
+            mountpoints.insert(entry.mountpoint, mountable);
+        }
+
         Ok(mountpoints)
     }
 
-    /// List installed applications (stub).
-    pub fn inspect_list_applications(&mut self, _root: &str) -> Result<Vec<Application>> {
+    /// fstab entries of the OS at `root`, with the devices they may refer to
+    pub(crate) fn fstab_entries(&mut self, root: &str) -> Option<(Vec<FstabEntry>, Vec<String>)> {
+        let was_mounted = self.mounted.contains_key(root);
+        if !was_mounted && self.mount_ro(root, "/").is_err() {
+            return None;
+        }
+        let fstab = self.cat("/etc/fstab");
+        if !was_mounted {
+            let _ = self.umount(root);
+        }
+        let fstab = fstab.ok()?;
+
+        let mut devices: Vec<String> = self
+            .list_filesystems()
+            .map(|filesystems| filesystems.into_keys().collect())
+            .unwrap_or_default();
+        devices.extend(
+            self.lvs()
+                .unwrap_or_default()
+                .iter()
+                .map(|lv| lv.trim().to_string()),
+        );
+
+        Some((fstab.lines().filter_map(FstabEntry::parse).collect(), devices))
+    }
+
+    /// Device among `devices` an fstab spec refers to.
+    pub(crate) fn resolve_fstab_spec(&mut self, spec: &str, devices: &[String]) -> Option<String> {
+        let spec = spec.trim_matches('"');
+
+        if let Some(uuid) = spec
+            .strip_prefix("UUID=")
+            .or_else(|| spec.strip_prefix("/dev/disk/by-uuid/"))
+        {
+            let uuid = uuid.trim_matches('"');
+            return devices
+                .iter()
+                .find(|device| {
+                    self.vfs_uuid(device)
+                        .is_ok_and(|u| u.eq_ignore_ascii_case(uuid))
+                })
+                .cloned();
+        }
+        if let Some(label) = spec
+            .strip_prefix("LABEL=")
+            .or_else(|| spec.strip_prefix("/dev/disk/by-label/"))
+        {
+            let label = label.trim_matches('"');
+            return devices
+                .iter()
+                .find(|device| self.vfs_label(device).is_ok_and(|l| l == label))
+                .cloned();
+        }
+
+        let name = guest_device_name(spec);
+        devices
+            .iter()
+            .find(|device| *device == spec)
+            .or_else(|| devices.iter().find(|device| **device == name))
+            .cloned()
+    }
+
+    /// List installed applications.
+    ///
+    /// Full records come from the apk, pacman and portage databases; dpkg
+    /// and rpm packages carry name, version and release.
+    pub fn inspect_list_applications(&mut self, root: &str) -> Result<Vec<Application>> {
         self.ensure_ready()?;
-        Ok(Vec::new())
+
+        match self.inspect_get_package_format(root)?.as_str() {
+            "deb" => self.dpkg_applications(),
+            "rpm" => self.rpm_applications(),
+            "apk" => self.apk_list(),
+            "pacman" => self.pacman_list(),
+            "ebuild" => self.portage_list(),
+            _ => Ok(self
+                .inspect_list_applications2(root)?
+                .into_iter()
+                .map(|(name, version, release)| Application {
+                    display_name: name.clone(),
+                    name,
+                    version,
+                    release,
+                    ..Default::default()
+                })
+                .collect()),
+        }
     }
 
-    /// Check if this is a live CD/USB (stub).
-    pub fn inspect_is_live(&mut self, _root: &str) -> Result<bool> {
+    /// Check if this is a live CD/USB, whose root is an image on the media.
+    pub(crate) fn inspect_is_live_untraced(&mut self, root: &str) -> Result<bool> {
         self.ensure_ready()?;
-        Ok(false)
+        Ok(root.starts_with(LIVEIMG_PREFIX))
     }
 }
 
 /// Installed application information
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct Application {
     pub name: String,
     pub display_name: String,
@@ -756,6 +1044,23 @@ pub struct Application {
     pub publisher: String,
     pub url: String,
     pub description: String,
+    /// Source package, where the database records one (dpkg `Source`,
+    /// apk origin, RPM source package)
+    pub source: String,
+}
+
+impl Application {
+    /// Version as the package manager writes it, `[epoch:]version[-release]`
+    pub fn full_version(&self) -> String {
+        let mut version = self.version.clone();
+        if self.epoch > 0 {
+            version = format!("{}:{}", self.epoch, version);
+        }
+        if !self.release.is_empty() {
+            version = format!("{}-{}", version, self.release);
+        }
+        version
+    }
 }
 
 /// Parsed /etc/os-release information
@@ -860,6 +1165,57 @@ fn build_partition_path(disk: &str, part_num: u32) -> String {
     }
 }
 
+/// Subvolumes that may hold an OS root, most likely first.
+///
+/// The usual root subvolume names come first (listing subvolumes needs the
+/// btrfs tool, so they are tried either way), then the other subvolumes of
+/// the top level. Snapshots are left out.
+fn root_subvolume_candidates(subvolumes: &[BtrfsSubvolume]) -> Vec<String> {
+    let mut candidates: Vec<String> = ROOT_SUBVOLUMES
+        .iter()
+        .map(|name| name.to_string())
+        .collect();
+    candidates.extend(
+        subvolumes
+            .iter()
+            .filter(|subvolume| {
+                subvolume.top_level == BTRFS_TOP_LEVEL_ID && !subvolume.is_snapshot()
+            })
+            .filter(|subvolume| !ROOT_SUBVOLUMES.contains(&subvolume.path.as_str()))
+            .map(|subvolume| subvolume.path.clone()),
+    );
+    candidates
+}
+
+/// Name of a guest device here.
+///
+/// Disks are `/dev/sdX` whatever bus the guest saw them on, and LVM volumes
+/// are `/dev/VG/LV` rather than `/dev/mapper/VG-LV`.
+fn guest_device_name(spec: &str) -> String {
+    for prefix in ["/dev/vd", "/dev/xvd", "/dev/hd"] {
+        if let Some(rest) = spec.strip_prefix(prefix) {
+            return format!("/dev/sd{}", rest);
+        }
+    }
+
+    if let Some(name) = spec.strip_prefix("/dev/mapper/") {
+        // Dashes in VG and LV names are doubled; the single one separates them
+        let bytes = name.as_bytes();
+        let separator = (0..bytes.len()).find(|&i| {
+            bytes[i] == b'-' && (i == 0 || bytes[i - 1] != b'-') && bytes.get(i + 1) != Some(&b'-')
+        });
+        if let Some(i) = separator {
+            return format!(
+                "/dev/{}/{}",
+                name[..i].replace("--", "-"),
+                name[i + 1..].replace("--", "-")
+            );
+        }
+    }
+
+    spec.to_string()
+}
+
 /// Strong Linux root markers.
 /// Keep this strict-ish to reduce false positives.
 fn looks_like_linux_root(g: &mut Guestfs) -> bool {
@@ -870,7 +1226,7 @@ fn looks_like_linux_root(g: &mut Guestfs) -> bool {
 
 /// Strong Windows root markers.
 /// Keep strict-ish to avoid NTFS data volumes being misclassified.
-fn looks_like_windows_root(g: &mut Guestfs) -> bool {
+pub(super) fn looks_like_windows_root(g: &mut Guestfs) -> bool {
     // Case-insensitive filesystems make this fairly robust.
     let win = g.exists("/Windows/System32").unwrap_or(false) || g.exists("/WINDOWS/System32").unwrap_or(false);
     let hive = g.exists("/Windows/System32/config").unwrap_or(false)
@@ -884,6 +1240,28 @@ fn looks_like_windows_root(g: &mut Guestfs) -> bool {
 mod tests {
     use super::*;
 
+    #[test]
+    fn test_select_os_root() {
+        let detected = vec!["/dev/sda2".to_string(), "/dev/sda5".to_string()];
+
+        let mut roots = detected.clone();
+        select_os_root(&mut roots, "/dev/sda5").unwrap();
+        assert_eq!(roots, ["/dev/sda5", "/dev/sda2"]);
+
+        let mut roots = detected.clone();
+        select_os_root(&mut roots, "2").unwrap();
+        assert_eq!(roots, ["/dev/sda5", "/dev/sda2"]);
+
+        let mut roots = detected.clone();
+        select_os_root(&mut roots, "1").unwrap();
+        assert_eq!(roots, detected);
+
+        let mut roots = detected.clone();
+        assert!(select_os_root(&mut roots, "3").is_err());
+        assert!(select_os_root(&mut roots, "/dev/sdb1").is_err());
+        assert_eq!(roots, detected);
+    }
+
     #[test]
     fn test_partition_path_builder() {
         assert_eq!(build_partition_path("/dev/sda", 1), "/dev/sda1");
@@ -892,6 +1270,44 @@ mod tests {
         assert_eq!(build_partition_path("/dev/mmcblk0", 1), "/dev/mmcblk0p1");
     }
 
+    #[test]
+    fn test_guest_device_name() {
+        assert_eq!(guest_device_name("/dev/vda2"), "/dev/sda2");
+        assert_eq!(guest_device_name("/dev/xvdb1"), "/dev/sdb1");
+        assert_eq!(guest_device_name("/dev/sda1"), "/dev/sda1");
+        assert_eq!(guest_device_name("/dev/mapper/rhel-root"), "/dev/rhel/root");
+        assert_eq!(
+            guest_device_name("/dev/mapper/vg--data-lv--home"),
+            "/dev/vg-data/lv-home"
+        );
+    }
+
+    #[test]
+    fn test_root_subvolume_candidates() {
+        let subvolume = |id, path: &str, top_level, parent_uuid: Option<&str>| BtrfsSubvolume {
+            id,
+            path: path.to_string(),
+            top_level,
+            uuid: None,
+            parent_uuid: parent_uuid.map(str::to_string),
+        };
+        let subvolumes = vec![
+            subvolume(256, "@home", 5, None),
+            subvolume(257, "@", 5, None),
+            subvolume(258, "fedora", 5, None),
+            subvolume(259, "@/var/lib/portables", 257, None),
+            subvolume(260, "@-backup", 5, Some("1234")),
+        ];
+
+        let candidates = root_subvolume_candidates(&subvolumes);
+        assert_eq!(candidates[0], "@");
+        assert!(candidates.contains(&"fedora".to_string()));
+        assert!(candidates.contains(&"@home".to_string()));
+        assert!(!candidates.contains(&"@/var/lib/portables".to_string()));
+        assert!(!candidates.contains(&"@-backup".to_string()));
+        assert_eq!(candidates.iter().filter(|c| *c == "@").count(), 1);
+    }
+
     #[test]
     fn test_os_release_parse_photon() {
         let content = r#"
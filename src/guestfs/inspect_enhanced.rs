@@ -1,7 +1,8 @@
 // SPDX-License-Identifier: LGPL-3.0-or-later
 //! Enhanced inspection operations for comprehensive guest analysis
 use crate::core::Result;
-use crate::guestfs::Guestfs;
+use crate::guestfs::windows::WindowsScheduledTask;
+use crate::guestfs::{Application, Guestfs};
 use serde::{Deserialize, Serialize};
 use serde_yaml::Value;
 use std::collections::HashMap;
@@ -38,10 +39,22 @@ pub struct NetworkInterface {
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct UserAccount {
     pub username: String,
+    /// UID, or the relative ID of a Windows account (500 for Administrator)
     pub uid: String,
     pub gid: String,
     pub home: String,
     pub shell: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub full_name: Option<String>,
+    /// Local groups (Windows)
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub groups: Vec<String>,
+    /// Account is disabled (Windows)
+    #[serde(default)]
+    pub disabled: bool,
+    /// Last logon time in UTC (Windows SAM, Linux lastlog)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub last_logon: Option<String>,
 }
 
 /// System service information
@@ -151,7 +164,7 @@ impl Guestfs {
     ///
     /// # Returns
     /// Vector of NetworkInterface with name, IPs, MAC, DHCP, and DNS servers
-    pub fn inspect_network(&mut self, root: &str) -> Result<Vec<NetworkInterface>> {
+    pub(crate) fn inspect_network_untraced(&mut self, root: &str) -> Result<Vec<NetworkInterface>> {
         self.with_mount(root, |guestfs| {
             let mut interfaces = Vec::new();
 
@@ -543,7 +556,7 @@ impl Guestfs {
     ///
     /// # Returns
     /// Deduplicated and sorted list of DNS server addresses
-    pub fn inspect_dns(&mut self, root: &str) -> Result<Vec<String>> {
+    pub(crate) fn inspect_dns_untraced(&mut self, root: &str) -> Result<Vec<String>> {
         self.with_mount(root, |guestfs| {
             let mut dns_servers = Vec::new();
 
@@ -686,19 +699,40 @@ impl Guestfs {
     }
 
     /// List user accounts
-    pub fn inspect_users(&mut self, root: &str) -> Result<Vec<UserAccount>> {
+    ///
+    /// Reads /etc/passwd, or the local accounts in the SAM hive on Windows.
+    pub(crate) fn inspect_users_untraced(&mut self, root: &str) -> Result<Vec<UserAccount>> {
         self.with_mount(root, |guestfs| {
+            if super::inspect::looks_like_windows_root(guestfs) {
+                return guestfs.inspect_windows_users(root);
+            }
+
+            let last_logons: HashMap<String, String> = guestfs
+                .last_logins()
+                .unwrap_or_default()
+                .into_iter()
+                .filter_map(|login| {
+                    let time = chrono::DateTime::from_timestamp(login.time, 0)?;
+                    Some((login.user, time.format("%Y-%m-%d %H:%M:%S").to_string()))
+                })
+                .collect();
+
             let mut users = Vec::new();
             if let Ok(content) = guestfs.cat(PASSWD) {
                 for line in content.lines() {
                     let parts: Vec<&str> = line.split(':').collect();
                     if parts.len() >= 7 {
+                        let gecos = parts[4].split(',').next().unwrap_or("").trim();
                         users.push(UserAccount {
                             username: parts[0].to_string(),
                             uid: parts[2].to_string(),
                             gid: parts[3].to_string(),
                             home: parts[5].to_string(),
                             shell: parts[6].to_string(),
+                            full_name: (!gecos.is_empty()).then(|| gecos.to_string()),
+                            groups: Vec::new(),
+                            disabled: false,
+                            last_logon: last_logons.get(parts[0]).cloned(),
                         });
                     }
                 }
@@ -708,7 +742,10 @@ impl Guestfs {
     }
 
     /// Get SSH configuration
-    pub fn inspect_ssh_config(&mut self, root: &str) -> Result<HashMap<String, String>> {
+    pub(crate) fn inspect_ssh_config_untraced(
+        &mut self,
+        root: &str,
+    ) -> Result<HashMap<String, String>> {
         self.with_mount(root, |guestfs| {
             let mut config = HashMap::new();
             if let Ok(content) = guestfs.cat(SSHD_CONFIG) {
@@ -727,7 +764,7 @@ impl Guestfs {
     }
 
     /// Check SELinux status
-    pub fn inspect_selinux(&mut self, root: &str) -> Result<String> {
+    pub(crate) fn inspect_selinux_untraced(&mut self, root: &str) -> Result<String> {
         self.with_mount(root, |guestfs| {
             if let Ok(content) = guestfs.cat(SELINUX_CONFIG) {
                 for line in content.lines() {
@@ -743,7 +780,10 @@ impl Guestfs {
     }
 
     /// List systemd services
-    pub fn inspect_systemd_services(&mut self, root: &str) -> Result<Vec<SystemService>> {
+    pub(crate) fn inspect_systemd_services_untraced(
+        &mut self,
+        root: &str,
+    ) -> Result<Vec<SystemService>> {
         self.with_mount(root, |guestfs| {
             let mut services = Vec::new();
             // Check for enabled services in /etc/systemd/system
@@ -761,7 +801,7 @@ impl Guestfs {
     }
 
     /// Get timezone information
-    pub fn inspect_timezone(&mut self, root: &str) -> Result<String> {
+    pub(crate) fn inspect_timezone_untraced(&mut self, root: &str) -> Result<String> {
         self.with_mount(root, |guestfs| {
             if let Ok(content) = guestfs.cat(TIMEZONE) {
                 Ok(content.trim().to_string())
@@ -782,7 +822,7 @@ impl Guestfs {
     }
 
     /// Get locale information
-    pub fn inspect_locale(&mut self, root: &str) -> Result<String> {
+    pub(crate) fn inspect_locale_untraced(&mut self, root: &str) -> Result<String> {
         self.with_mount(root, |guestfs| {
             if let Ok(content) = guestfs.cat(LOCALE_CONF) {
                 for line in content.lines() {
@@ -810,7 +850,7 @@ impl Guestfs {
     }
 
     /// Detect LVM configuration
-    pub fn inspect_lvm(&mut self, _root: &str) -> Result<LVMInfo> {
+    pub(crate) fn inspect_lvm_untraced(&mut self, _root: &str) -> Result<LVMInfo> {
         let mut lvm_info = LVMInfo {
             physical_volumes: Vec::new(),
             volume_groups: Vec::new(),
@@ -953,7 +993,7 @@ impl Guestfs {
     }
 
     /// Detect cloud-init
-    pub fn inspect_cloud_init(&mut self, root: &str) -> Result<bool> {
+    pub(crate) fn inspect_cloud_init_untraced(&mut self, root: &str) -> Result<bool> {
         self.with_mount(root, |guestfs| {
             Ok(guestfs.exists("/etc/cloud/cloud.cfg").unwrap_or(false)
                 || guestfs.exists("/usr/bin/cloud-init").unwrap_or(false))
@@ -961,7 +1001,10 @@ impl Guestfs {
     }
 
     /// Get language runtime versions
-    pub fn inspect_runtimes(&mut self, root: &str) -> Result<HashMap<String, String>> {
+    pub(crate) fn inspect_runtimes_untraced(
+        &mut self,
+        root: &str,
+    ) -> Result<HashMap<String, String>> {
         self.with_mount(root, |guestfs| {
             let mut runtimes = HashMap::new();
             // Python
@@ -996,7 +1039,10 @@ impl Guestfs {
     }
 
     /// Detect container runtimes
-    pub fn inspect_container_runtimes(&mut self, root: &str) -> Result<Vec<String>> {
+    pub(crate) fn inspect_container_runtimes_untraced(
+        &mut self,
+        root: &str,
+    ) -> Result<Vec<String>> {
         self.with_mount(root, |guestfs| {
             let mut runtimes = Vec::new();
             if guestfs.exists("/usr/bin/docker").unwrap_or(false) {
@@ -1018,7 +1064,7 @@ impl Guestfs {
     }
 
     /// List cron jobs
-    pub fn inspect_cron(&mut self, root: &str) -> Result<Vec<String>> {
+    pub(crate) fn inspect_cron_untraced(&mut self, root: &str) -> Result<Vec<String>> {
         self.with_mount(root, |guestfs| {
             let mut cron_jobs = Vec::new();
             // System crontab
@@ -1053,7 +1099,7 @@ impl Guestfs {
     }
 
     /// List systemd timers
-    pub fn inspect_systemd_timers(&mut self, root: &str) -> Result<Vec<String>> {
+    pub(crate) fn inspect_systemd_timers_untraced(&mut self, root: &str) -> Result<Vec<String>> {
         self.with_mount(root, |guestfs| {
             let mut timers = Vec::new();
             if let Ok(links) = guestfs.ls(SYSTEMD_TIMERS_DIR) {
@@ -1067,7 +1113,7 @@ impl Guestfs {
     }
 
     /// List SSL certificates
-    pub fn inspect_certificates(&mut self, root: &str) -> Result<Vec<Certificate>> {
+    pub(crate) fn inspect_certificates_untraced(&mut self, root: &str) -> Result<Vec<Certificate>> {
         self.with_mount(root, |guestfs| {
             let mut certs = Vec::new();
             // Common certificate locations
@@ -1115,7 +1161,10 @@ impl Guestfs {
     }
 
     /// Get kernel parameters
-    pub fn inspect_kernel_params(&mut self, root: &str) -> Result<HashMap<String, String>> {
+    pub(crate) fn inspect_kernel_params_untraced(
+        &mut self,
+        root: &str,
+    ) -> Result<HashMap<String, String>> {
         self.with_mount(root, |guestfs| {
             let mut params = HashMap::new();
             if let Ok(content) = guestfs.cat(SYSCTL_CONF) {
@@ -1134,7 +1183,7 @@ impl Guestfs {
     }
 
     /// Detect virtualization guest tools
-    pub fn inspect_vm_tools(&mut self, root: &str) -> Result<Vec<String>> {
+    pub(crate) fn inspect_vm_tools_untraced(&mut self, root: &str) -> Result<Vec<String>> {
         self.with_mount(root, |guestfs| {
             let mut tools = Vec::new();
             // VMware Tools
@@ -1222,7 +1271,7 @@ impl Guestfs {
     }
 
     /// Get swap information
-    pub fn inspect_swap(&mut self, root: &str) -> Result<Vec<String>> {
+    pub(crate) fn inspect_swap_untraced(&mut self, root: &str) -> Result<Vec<String>> {
         self.with_mount(root, |guestfs| {
             let mut swap_devices = Vec::new();
             if let Ok(content) = guestfs.cat(FSTAB) {
@@ -1242,7 +1291,10 @@ impl Guestfs {
     }
 
     /// Get fstab mounts
-    pub fn inspect_fstab(&mut self, root: &str) -> Result<Vec<(String, String, String)>> {
+    pub(crate) fn inspect_fstab_untraced(
+        &mut self,
+        root: &str,
+    ) -> Result<Vec<(String, String, String)>> {
         self.with_mount(root, |guestfs| {
             let mut mounts = Vec::new();
             if let Ok(content) = guestfs.cat(FSTAB) {
@@ -1314,36 +1366,28 @@ impl Guestfs {
                     }
                 }
             }
-            // Arch Linux
-            else if guestfs.exists("/usr/bin/pacman").unwrap_or(false) {
-                pkg_info.manager = "pacman".to_string();
-                if let Ok(output) = guestfs.command(&["pacman", "-Q"]) {
-                    for line in output.lines() {
-                        let parts: Vec<&str> = line.split_whitespace().collect();
-                        if parts.len() >= 2 {
-                            pkg_info.packages.push(Package {
-                                name: parts[0].to_string(),
-                                version: parts[1].to_string(),
-                                manager: "pacman".to_string(),
-                            });
-                        }
+            // Arch Linux, Alpine and Gentoo, read from their databases
+            else {
+                type List = fn(&mut Guestfs) -> Result<Vec<Application>>;
+                let native: [(&str, &str, List); 3] = [
+                    ("pacman", "/var/lib/pacman/local", Guestfs::pacman_list),
+                    ("apk", "/lib/apk/db/installed", Guestfs::apk_list),
+                    ("portage", "/var/db/pkg", Guestfs::portage_list),
+                ];
+                for (manager, database, list) in native {
+                    if !guestfs.exists(database).unwrap_or(false) {
+                        continue;
                     }
-                }
-            }
-            // Alpine Linux
-            else if guestfs.exists("/sbin/apk").unwrap_or(false) {
-                pkg_info.manager = "apk".to_string();
-                if let Ok(output) = guestfs.command(&["apk", "info", "-v"]) {
-                    for line in output.lines() {
-                        // Format: package-version
-                        if let Some(pos) = line.rfind('-') {
-                            pkg_info.packages.push(Package {
-                                name: line[..pos].to_string(),
-                                version: line[pos+1..].to_string(),
-                                manager: "apk".to_string(),
-                            });
-                        }
+                    let packages = list(guestfs);
+                    pkg_info.manager = manager.to_string();
+                    for package in packages.unwrap_or_default() {
+                        pkg_info.packages.push(Package {
+                            version: package.full_version(),
+                            name: package.name,
+                            manager: manager.to_string(),
+                        });
                     }
+                    break;
                 }
             }
 
@@ -1735,8 +1779,90 @@ impl Guestfs {
     }
 
     // ==================== Windows-Specific Inspection ====================
+    /// Inspect Windows version and identity from registry
+    ///
+    /// Reads product name, edition and build from the SOFTWARE hive, and the
+    /// computer name and current control set from the SYSTEM hive.
+    pub(crate) fn inspect_windows_system_untraced(
+        &mut self,
+        root: &str,
+    ) -> Result<WindowsSystemInfo> {
+        let was_mounted = self.mounted.contains_key("/");
+        if !was_mounted {
+            self.mount_ro(root, "/")?;
+        }
+        let systemroot = self
+            .inspect_get_windows_systemroot(root)
+            .unwrap_or_else(|_| "/Windows".to_string());
+        let software_path = format!("{}/System32/config/SOFTWARE", systemroot);
+        let system_path = format!("{}/System32/config/SYSTEM", systemroot);
+        let os_info = self
+            .resolve_guest_path(&software_path)
+            .and_then(|path| super::windows_registry::get_windows_os_info(&path));
+        let system_host = self.resolve_guest_path(&system_path).ok();
+        let computer_name = system_host
+            .as_ref()
+            .and_then(|path| super::windows_registry::get_computer_name(path).ok());
+        let current_control_set = system_host
+            .as_ref()
+            .and_then(|path| super::windows_registry::get_current_control_set(path).ok());
+        if !was_mounted {
+            self.umount("/").ok();
+        }
+        let os_info = os_info?;
+        Ok(WindowsSystemInfo {
+            product_name: os_info.product_name,
+            edition: os_info.edition,
+            version: os_info.version,
+            build_number: os_info.build_number,
+            display_version: os_info.display_version,
+            computer_name,
+            current_control_set,
+        })
+    }
+
+    /// Local accounts from the SAM hive
+    ///
+    /// Home directories come from the profile list in the SOFTWARE hive;
+    /// accounts that never logged on have none. Expects the root to be
+    /// mounted.
+    fn inspect_windows_users(&mut self, root: &str) -> Result<Vec<UserAccount>> {
+        let systemroot = self
+            .inspect_get_windows_systemroot(root)
+            .unwrap_or_else(|_| "/Windows".to_string());
+        let sam_path = self.resolve_guest_path(&format!("{}/System32/config/SAM", systemroot))?;
+        let users = super::windows_registry::parse_local_users(&sam_path)?;
+        let profiles = self
+            .resolve_guest_path(&format!("{}/System32/config/SOFTWARE", systemroot))
+            .and_then(|path| super::windows_registry::parse_profile_paths(&path))
+            .unwrap_or_default();
+
+        Ok(users
+            .into_iter()
+            .map(|user| UserAccount {
+                home: user
+                    .sid
+                    .as_ref()
+                    .and_then(|sid| profiles.get(sid))
+                    .cloned()
+                    .unwrap_or_default(),
+                username: user.name,
+                uid: user.rid.to_string(),
+                gid: String::new(),
+                shell: String::new(),
+                full_name: user.full_name,
+                groups: user.groups,
+                disabled: user.disabled,
+                last_logon: user.last_logon,
+            })
+            .collect())
+    }
+
     /// Inspect Windows software from registry
-    pub fn inspect_windows_software(&mut self, root: &str) -> Result<Vec<WindowsApplication>> {
+    pub(crate) fn inspect_windows_software_untraced(
+        &mut self,
+        root: &str,
+    ) -> Result<Vec<WindowsApplication>> {
         let mut applications = Vec::new();
         let was_mounted = self.mounted.contains_key("/");
         if !was_mounted {
@@ -1758,7 +1884,7 @@ impl Guestfs {
                     name: app.name,
                     version: app.version,
                     publisher: app.publisher,
-                    install_date: "Unknown".to_string(),
+                    install_date: app.install_date.unwrap_or_else(|| "Unknown".to_string()),
                 });
             }
         }
@@ -1769,7 +1895,10 @@ impl Guestfs {
     }
 
     /// Inspect Windows services from registry
-    pub fn inspect_windows_services(&mut self, root: &str) -> Result<Vec<WindowsService>> {
+    pub(crate) fn inspect_windows_services_untraced(
+        &mut self,
+        root: &str,
+    ) -> Result<Vec<WindowsService>> {
         let mut services = Vec::new();
         let was_mounted = self.mounted.contains_key("/");
         if !was_mounted {
@@ -1790,6 +1919,10 @@ impl Guestfs {
                     display_name: svc.display_name,
                     start_type: svc.start_type,
                     status: "Unknown".to_string(), // Status requires runtime info
+                    service_type: svc.service_type,
+                    image_path: svc.image_path,
+                    account: svc.account,
+                    description: svc.description,
                 });
             }
         }
@@ -1799,8 +1932,19 @@ impl Guestfs {
         Ok(services)
     }
 
+    /// Inspect Windows scheduled tasks
+    pub(crate) fn inspect_windows_scheduled_tasks_untraced(
+        &mut self,
+        root: &str,
+    ) -> Result<Vec<WindowsScheduledTask>> {
+        self.with_mount(root, |guestfs| guestfs.inspect_list_windows_scheduled_tasks(root))
+    }
+
     /// Inspect Windows network configuration
-    pub fn inspect_windows_network(&mut self, root: &str) -> Result<Vec<WindowsNetworkAdapter>> {
+    pub(crate) fn inspect_windows_network_untraced(
+        &mut self,
+        root: &str,
+    ) -> Result<Vec<WindowsNetworkAdapter>> {
         let mut adapters = Vec::new();
         let was_mounted = self.mounted.contains_key("/");
         if !was_mounted {
@@ -1835,7 +1979,10 @@ impl Guestfs {
     }
 
     /// Inspect Windows updates and patches
-    pub fn inspect_windows_updates(&mut self, root: &str) -> Result<Vec<WindowsUpdate>> {
+    pub(crate) fn inspect_windows_updates_untraced(
+        &mut self,
+        root: &str,
+    ) -> Result<Vec<WindowsUpdate>> {
         let mut updates = Vec::new();
         let was_mounted = self.mounted.contains_key("/");
         if !was_mounted {
@@ -1866,6 +2013,10 @@ impl Guestfs {
         if let Ok(content) = self.cat(&cbs_log_path) {
             let cbs_updates = super::windows_registry::parse_cbs_log(&content);
             for upd in cbs_updates {
+                // Prefer the registry entry, which has the install date
+                if updates.iter().any(|u| u.kb == upd.kb_number) {
+                    continue;
+                }
                 updates.push(WindowsUpdate {
                     kb: upd.kb_number,
                     title: upd.title,
@@ -1915,7 +2066,7 @@ impl Guestfs {
     }
 
     /// Inspect Windows event logs
-    pub fn inspect_windows_events(
+    pub(crate) fn inspect_windows_events_untraced(
         &mut self,
         root: &str,
         log_name: &str,
@@ -1956,6 +2107,18 @@ impl Guestfs {
     }
 }
 
+/// Windows version and identity
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowsSystemInfo {
+    pub product_name: String,
+    pub edition: String,
+    pub version: String,
+    pub build_number: String,
+    pub display_version: Option<String>,
+    pub computer_name: Option<String>,
+    pub current_control_set: Option<String>,
+}
+
 /// Windows application information
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WindowsApplication {
@@ -1972,6 +2135,10 @@ pub struct WindowsService {
     pub display_name: String,
     pub start_type: String,
     pub status: String,
+    pub service_type: String,
+    pub image_path: String,
+    pub account: Option<String>,
+    pub description: Option<String>,
 }
 
 /// Windows network adapter information
@@ -9,7 +9,7 @@ use crate::guestfs::Guestfs;
 impl Guestfs {
     /// Get operating system product variant
     ///
-    pub fn inspect_get_product_variant(&mut self, root: &str) -> Result<String> {
+    pub(crate) fn inspect_get_product_variant_untraced(&mut self, root: &str) -> Result<String> {
         self.ensure_ready()?;
 
         if self.verbose {
@@ -43,7 +43,7 @@ impl Guestfs {
 
     /// Get format of OS
     ///
-    pub fn inspect_get_format(&mut self, root: &str) -> Result<String> {
+    pub(crate) fn inspect_get_format_untraced(&mut self, root: &str) -> Result<String> {
         self.ensure_ready()?;
 
         if self.verbose {
@@ -62,7 +62,7 @@ impl Guestfs {
 
     /// Check if multipart OS
     ///
-    pub fn inspect_is_multipart(&mut self, root: &str) -> Result<bool> {
+    pub(crate) fn inspect_is_multipart_untraced(&mut self, root: &str) -> Result<bool> {
         self.ensure_ready()?;
 
         if self.verbose {
@@ -77,7 +77,7 @@ impl Guestfs {
 
     /// Check if NetInstall
     ///
-    pub fn inspect_is_netinst(&mut self, root: &str) -> Result<bool> {
+    pub(crate) fn inspect_is_netinst_untraced(&mut self, root: &str) -> Result<bool> {
         self.ensure_ready()?;
 
         if self.verbose {
@@ -175,6 +175,11 @@ impl Guestfs {
                     }
                 }
             }
+            "apk" | "pacman" | "ebuild" => {
+                for pkg in self.inspect_list_applications(root)? {
+                    apps.push((pkg.name, pkg.version, pkg.release));
+                }
+            }
             _ => {}
         }
 
@@ -184,7 +189,7 @@ impl Guestfs {
     /// Get package management tool
     ///
     /// Additional functionality for package detection
-    pub fn inspect_get_package_management(&mut self, root: &str) -> Result<String> {
+    pub(crate) fn inspect_get_package_management_untraced(&mut self, root: &str) -> Result<String> {
         self.ensure_ready()?;
 
         if self.trace {
@@ -236,7 +241,7 @@ impl Guestfs {
     /// Get init system type
     ///
     /// Already exists as get_init_system, adding alias for inspection
-    pub fn inspect_get_init_system(&mut self, root: &str) -> Result<String> {
+    pub(crate) fn inspect_get_init_system_untraced(&mut self, root: &str) -> Result<String> {
         // Try to mount if not already mounted
         let was_mounted = self.mounted.contains_key("/");
         if !was_mounted {
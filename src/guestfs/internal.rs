@@ -45,7 +45,7 @@ impl Guestfs {
 
     /// Get free disk space
     ///
-    pub fn statvfs(&mut self, path: &str) -> Result<HashMap<String, i64>> {
+    pub(crate) fn statvfs_untraced(&mut self, path: &str) -> Result<HashMap<String, i64>> {
         self.ensure_ready()?;
 
         if self.verbose {
@@ -2,10 +2,185 @@
 //! ISO and CD-ROM operations for disk image manipulation
 //!
 //! This implementation provides ISO image handling.
+//!
+//! Live media and image based OSes keep their root filesystem as a
+//! squashfs or erofs image on an ISO9660 or ext4 carrier filesystem. Such
+//! roots are mounted through mountables of the form `liveimg:DEVICE/IMAGE`
+//! (e.g. `liveimg:/dev/sda1/LiveOS/squashfs.img`), which is what inspection
+//! returns for them.
 
 use crate::core::{Error, Result};
+use crate::guestfs::btrfs::split_device_path;
+use crate::guestfs::squashfs_ops::image_vfs_type;
 use crate::guestfs::Guestfs;
+use std::fs;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::atomic::{AtomicUsize, Ordering};
+
+/// Prefix of mountables naming a root image on live media
+pub const LIVEIMG_PREFIX: &str = "liveimg:";
+
+/// Where live media keep their root image, most common first
+const LIVE_ROOT_IMAGES: &[&str] = &[
+    // Fedora, RHEL and derivatives
+    "LiveOS/squashfs.img",
+    "LiveOS/rootfs.img",
+    "images/install.img",
+    // Ubuntu and Debian
+    "casper/filesystem.squashfs",
+    "casper/minimal.squashfs",
+    "live/filesystem.squashfs",
+    // Arch
+    "arch/x86_64/airootfs.sfs",
+    "arch/x86_64/airootfs.erofs",
+    // Kairos and other cOS based images, on their state and recovery partitions
+    "cOS/active.img",
+    "cOS/passive.img",
+    "cOS/recovery.img",
+    "cOS/recovery.squashfs",
+    // Appliance builders (buildroot, Yocto)
+    "rootfs.squashfs",
+];
+
+/// Older Fedora live media nest an ext4 root image in their squashfs image
+const NESTED_ROOT_IMAGE: &str = "LiveOS/rootfs.img";
+
+/// Carrier mounts made by this process so far, for unique mountpoints
+static CARRIERS: AtomicUsize = AtomicUsize::new(0);
+
+/// Mountable for root image `image` on the filesystem of `device`
+pub fn liveimg_mountable(device: &str, image: &str) -> String {
+    format!(
+        "{}{}/{}",
+        LIVEIMG_PREFIX,
+        device,
+        image.trim_start_matches('/')
+    )
+}
+
+/// Split a `liveimg:` mountable into device and image path
+///
+/// The image path is relative to the root of the filesystem on the device.
+pub fn parse_liveimg(mountable: &str) -> Option<(&str, &str)> {
+    let (device, image) = split_device_path(mountable.strip_prefix(LIVEIMG_PREFIX)?)?;
+    if image.is_empty() {
+        return None;
+    }
+    Some((device, image))
+}
+
+/// Mount `source` on `target` on the host, read-only unless `options` say
+fn host_mount(options: &str, source: &Path, target: &Path) -> Result<()> {
+    let mut cmd = if unsafe { libc::geteuid() } != 0 {
+        let mut sudo_cmd = Command::new("sudo");
+        sudo_cmd.arg("mount");
+        sudo_cmd
+    } else {
+        Command::new("mount")
+    };
+
+    let output = cmd
+        .arg("-o")
+        .arg(options)
+        .arg(source)
+        .arg(target)
+        .output()
+        .map_err(|e| Error::CommandFailed(format!("Failed to execute mount: {}", e)))?;
+
+    if !output.status.success() {
+        return Err(Error::CommandFailed(format!(
+            "Mount of {} failed: {}",
+            source.display(),
+            String::from_utf8_lossy(&output.stderr)
+        )));
+    }
+
+    Ok(())
+}
+
+/// Unmount a carrier mount and remove its mountpoint (best-effort)
+fn release_carrier(carrier: &Path) {
+    let mut cmd = if unsafe { libc::geteuid() } != 0 {
+        let mut sudo_cmd = Command::new("sudo");
+        sudo_cmd.arg("umount");
+        sudo_cmd
+    } else {
+        Command::new("umount")
+    };
+
+    match cmd.arg(carrier).output() {
+        Ok(output) if output.status.success() => {
+            let _ = fs::remove_dir(carrier);
+        }
+        Ok(output) => eprintln!(
+            "Warning: umount {} failed: {}",
+            carrier.display(),
+            String::from_utf8_lossy(&output.stderr)
+        ),
+        Err(e) => eprintln!("Warning: failed to execute umount: {}", e),
+    }
+}
+
+/// Unmount carrier mounts, innermost first
+pub(crate) fn release_carriers(carriers: &[PathBuf]) {
+    for carrier in carriers.iter().rev() {
+        release_carrier(carrier);
+    }
+}
+
+/// Mount `source` on a new carrier mountpoint, recorded in `carriers`
+fn mount_carrier(options: &str, source: &Path, carriers: &mut Vec<PathBuf>) -> Result<PathBuf> {
+    let carrier = PathBuf::from("/run").join(format!(
+        "guestctl-{}-live-{}",
+        std::process::id(),
+        CARRIERS.fetch_add(1, Ordering::Relaxed)
+    ));
+    fs::create_dir_all(&carrier)
+        .map_err(|e| Error::CommandFailed(format!("Failed to create mountpoint: {}", e)))?;
+
+    if let Err(e) = host_mount(options, source, &carrier) {
+        let _ = fs::remove_dir(&carrier);
+        return Err(e);
+    }
+    carriers.push(carrier.clone());
+    Ok(carrier)
+}
+
+/// Mount the carriers of `image` on `host_device`, returning the image file
+fn open_live_image(
+    fs_type: &str,
+    host_device: &Path,
+    image: &str,
+    carriers: &mut Vec<PathBuf>,
+) -> Result<PathBuf> {
+    let options = if fs_type.starts_with("ext") {
+        "ro,noload"
+    } else {
+        "ro"
+    };
+    let carrier = mount_carrier(options, host_device, carriers)?;
+
+    let file = carrier.join(image);
+    if !file.is_file() {
+        return Err(Error::NotFound(format!("No live image {}", image)));
+    }
+
+    if image_vfs_type(&file)? != Some("squashfs") {
+        return Ok(file);
+    }
+
+    // The root may be an ext4 image in the squashfs image
+    let inner = mount_carrier("loop,ro", &file, carriers)?;
+    let nested = inner.join(NESTED_ROOT_IMAGE);
+    if nested.is_file() && !inner.join("etc").is_dir() {
+        return Ok(nested);
+    }
+    if let Some(inner) = carriers.pop() {
+        release_carrier(&inner);
+    }
+    Ok(file)
+}
 
 impl Guestfs {
     /// Create ISO image from directory
@@ -163,6 +338,65 @@ impl Guestfs {
 
         Ok(())
     }
+
+    /// Root images on the live media filesystem of `device`
+    ///
+    /// Returns `liveimg:` mountables for the images found, which may or may
+    /// not hold an OS.
+    pub fn live_root_images(&mut self, device: &str) -> Result<Vec<String>> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: live_root_images {}", device);
+        }
+
+        let was_mounted = self.mounted.contains_key(device);
+        if !was_mounted {
+            self.mount_ro(device, "/")?;
+        }
+
+        let mountpoint = PathBuf::from(&self.mounted[device]);
+        let images = LIVE_ROOT_IMAGES
+            .iter()
+            .filter(|image| mountpoint.join(image).is_file())
+            .map(|image| liveimg_mountable(device, image))
+            .collect();
+
+        if !was_mounted {
+            let _ = self.umount(device);
+        }
+
+        Ok(images)
+    }
+
+    /// Mount the carrier filesystems of root image `image` on `device`
+    ///
+    /// `host_device` is the host node of `device`. Returns the image file to
+    /// loop mount, and the carrier mounts to release after unmounting it.
+    pub(crate) fn mount_live_image(
+        &mut self,
+        device: &str,
+        host_device: &Path,
+        image: &str,
+    ) -> Result<(PathBuf, Vec<PathBuf>)> {
+        let fs_type = self.vfs_type(device).unwrap_or_else(|_| "auto".to_string());
+
+        let mut carriers = Vec::new();
+        match open_live_image(&fs_type, host_device, image, &mut carriers) {
+            Ok(file) => Ok((file, carriers)),
+            Err(e) => {
+                release_carriers(&carriers);
+                Err(e)
+            }
+        }
+    }
+
+    /// Release the carrier mounts of an unmounted `liveimg:` mountable
+    pub(crate) fn release_live_carriers(&mut self, mountable: &str) {
+        if let Some(carriers) = self.live_carriers.remove(mountable) {
+            release_carriers(&carriers);
+        }
+    }
 }
 
 #[cfg(test)]
@@ -174,4 +408,26 @@ mod tests {
         let mut g = Guestfs::new().unwrap();
         // API structure tests
     }
+
+    #[test]
+    fn test_liveimg_mountable() {
+        let mountable = liveimg_mountable("/dev/sda1", "/LiveOS/squashfs.img");
+        assert_eq!(mountable, "liveimg:/dev/sda1/LiveOS/squashfs.img");
+        assert_eq!(
+            parse_liveimg(&mountable),
+            Some(("/dev/sda1", "LiveOS/squashfs.img"))
+        );
+        assert_eq!(
+            parse_liveimg("liveimg:/dev/sda/casper/filesystem.squashfs"),
+            Some(("/dev/sda", "casper/filesystem.squashfs"))
+        );
+        assert_eq!(
+            parse_liveimg("liveimg:/dev/mapper/live-rw/cOS/active.img"),
+            Some(("/dev/mapper/live-rw", "cOS/active.img"))
+        );
+        assert_eq!(parse_liveimg("liveimg:/dev/sda1"), None);
+        assert_eq!(parse_liveimg("liveimg:/dev/sda1/"), None);
+        assert_eq!(parse_liveimg("/dev/sda1"), None);
+        assert_eq!(parse_liveimg("btrfsvol:/dev/sda2/@"), None);
+    }
 }
@@ -0,0 +1,383 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Login accounting: wtmp, btmp and lastlog
+//!
+//! wtmp and btmp are arrays of glibc `struct utmp` records (384 bytes on
+//! every Linux architecture): wtmp logs logins, logouts, boots and
+//! shutdowns, btmp failed logins. lastlog holds one `struct lastlog` per
+//! UID at offset `uid * 292`, so it is read record by record for the UIDs
+//! in `/etc/passwd` rather than whole; the file is sparse and its apparent
+//! size can run to terabytes.
+
+use crate::core::{Error, Result};
+use crate::guestfs::Guestfs;
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+use std::io::Read;
+use std::net::{IpAddr, Ipv4Addr, Ipv6Addr};
+
+/// Size of `struct utmp`
+pub const UTMP_SIZE: usize = 384;
+
+/// Size of `struct lastlog`
+pub const LASTLOG_SIZE: usize = 292;
+
+const RUN_LVL: i16 = 1;
+const BOOT_TIME: i16 = 2;
+const NEW_TIME: i16 = 3;
+const OLD_TIME: i16 = 4;
+const LOGIN_PROCESS: i16 = 6;
+const USER_PROCESS: i16 = 7;
+const DEAD_PROCESS: i16 = 8;
+
+const LOG_DIR: &str = "/var/log";
+const LASTLOG: &str = "/var/log/lastlog";
+
+/// What a login accounting record says happened
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum LoginKind {
+    Login,
+    Logout,
+    FailedLogin,
+    Boot,
+    Shutdown,
+    RunLevel,
+    ClockChange,
+}
+
+impl LoginKind {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Login => "login",
+            Self::Logout => "logout",
+            Self::FailedLogin => "failed_login",
+            Self::Boot => "boot",
+            Self::Shutdown => "shutdown",
+            Self::RunLevel => "run_level",
+            Self::ClockChange => "clock_change",
+        }
+    }
+}
+
+/// A wtmp or btmp record
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LoginEvent {
+    pub kind: LoginKind,
+    /// Account name; for logouts, that of the login on the same line
+    pub user: String,
+    /// Terminal, e.g. `pts/0`, `tty1` or `ssh:notty`
+    pub line: String,
+    /// Remote host as logged, or the kernel release for boots
+    pub host: String,
+    /// Remote address, if one was logged
+    pub ip: Option<IpAddr>,
+    pub pid: i32,
+    /// Unix seconds
+    pub time: i64,
+    /// File the record came from
+    pub source: String,
+}
+
+impl LoginEvent {
+    /// Remote address, else remote host name; None for local logins
+    pub fn remote(&self) -> Option<String> {
+        // X displays log their display (`:0`) as the host
+        self.ip.map(|ip| ip.to_string()).or_else(|| {
+            (!self.host.is_empty() && !self.host.starts_with(':')).then(|| self.host.clone())
+        })
+    }
+
+    /// One-line description, e.g. `alice on pts/0 from 10.0.0.5`
+    pub fn describe(&self) -> String {
+        let mut text = match self.kind {
+            LoginKind::Boot => format!("system boot ({})", self.host),
+            LoginKind::Shutdown => "system shutdown".to_string(),
+            LoginKind::RunLevel => format!("run level {}", (self.pid & 0xff) as u8 as char),
+            LoginKind::ClockChange => "clock changed".to_string(),
+            _ => format!("{} on {}", self.user, self.line),
+        };
+        if matches!(
+            self.kind,
+            LoginKind::Login | LoginKind::Logout | LoginKind::FailedLogin
+        ) {
+            match (&self.ip, self.host.is_empty()) {
+                (Some(ip), true) => text.push_str(&format!(" from {}", ip)),
+                (Some(ip), false) if self.host != ip.to_string() => {
+                    text.push_str(&format!(" from {} ({})", self.host, ip))
+                }
+                (_, false) => text.push_str(&format!(" from {}", self.host)),
+                (None, true) => {}
+            }
+        }
+        text
+    }
+}
+
+/// An account's last login, from lastlog
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LastLogin {
+    pub user: String,
+    pub uid: u32,
+    /// Unix seconds
+    pub time: i64,
+    pub line: String,
+    pub host: String,
+}
+
+/// NUL-terminated string field
+fn c_string(data: &[u8]) -> String {
+    let end = data.iter().position(|b| *b == 0).unwrap_or(data.len());
+    String::from_utf8_lossy(&data[..end]).trim().to_string()
+}
+
+fn le_i32(data: &[u8], offset: usize) -> i32 {
+    i32::from_le_bytes(data[offset..offset + 4].try_into().unwrap())
+}
+
+/// `ut_addr_v6`: an IPv4 address in the first word, or an IPv6 address
+fn utmp_address(data: &[u8]) -> Option<IpAddr> {
+    if data[4..16].iter().all(|b| *b == 0) {
+        let v4: [u8; 4] = data[..4].try_into().ok()?;
+        (v4 != [0; 4]).then(|| IpAddr::V4(Ipv4Addr::from(v4)))
+    } else {
+        let v6: [u8; 16] = data.try_into().ok()?;
+        Some(IpAddr::V6(Ipv6Addr::from(v6)))
+    }
+}
+
+/// Parse wtmp (`failed` false) or btmp (`failed` true) records
+pub fn parse_utmp(data: &[u8], failed: bool, source: &str) -> Vec<LoginEvent> {
+    let mut events = Vec::new();
+    for record in data.chunks_exact(UTMP_SIZE) {
+        let ut_type = i16::from_le_bytes([record[0], record[1]]);
+        let user = c_string(&record[44..76]);
+        let kind = match ut_type {
+            LOGIN_PROCESS | USER_PROCESS if failed => LoginKind::FailedLogin,
+            _ if failed => continue,
+            USER_PROCESS => LoginKind::Login,
+            DEAD_PROCESS => LoginKind::Logout,
+            BOOT_TIME => LoginKind::Boot,
+            RUN_LVL if user == "shutdown" => LoginKind::Shutdown,
+            RUN_LVL => LoginKind::RunLevel,
+            NEW_TIME | OLD_TIME => LoginKind::ClockChange,
+            _ => continue,
+        };
+        let time = le_i32(record, 340) as i64;
+        if time <= 0 {
+            continue;
+        }
+        events.push(LoginEvent {
+            kind,
+            user,
+            line: c_string(&record[8..40]),
+            host: c_string(&record[76..332]),
+            ip: utmp_address(&record[348..364]),
+            pid: le_i32(record, 4),
+            time,
+            source: source.to_string(),
+        });
+    }
+    events
+}
+
+/// Parse one lastlog record: time, line and host, if the account ever
+/// logged in
+pub fn parse_lastlog(record: &[u8]) -> Option<(i64, String, String)> {
+    if record.len() < LASTLOG_SIZE {
+        return None;
+    }
+    let time = le_i32(record, 0) as u32 as i64;
+    (time != 0).then(|| (time, c_string(&record[4..36]), c_string(&record[36..292])))
+}
+
+/// Give logouts the user of the login they end
+fn match_logouts(events: &mut [LoginEvent]) {
+    let mut sessions: HashMap<String, String> = HashMap::new();
+    for event in events.iter_mut() {
+        match event.kind {
+            LoginKind::Login => {
+                sessions.insert(event.line.clone(), event.user.clone());
+            }
+            LoginKind::Logout if event.user.is_empty() => {
+                if let Some(user) = sessions.remove(&event.line) {
+                    event.user = user;
+                }
+            }
+            LoginKind::Boot => sessions.clear(),
+            _ => {}
+        }
+    }
+}
+
+impl Guestfs {
+    /// Logins, logouts, boots and shutdowns from wtmp and failed logins
+    /// from btmp, rotated and gzip-compressed copies included, oldest first
+    pub fn login_history(&mut self) -> Result<Vec<LoginEvent>> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: login_history");
+        }
+
+        let mut names: Vec<String> = self
+            .ls(LOG_DIR)
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|name| name.starts_with("wtmp") || name.starts_with("btmp"))
+            .collect();
+        names.sort();
+
+        let mut events = Vec::new();
+        for name in names {
+            let path = format!("{}/{}", LOG_DIR, name);
+            if !self.is_file(&path).unwrap_or(false) {
+                continue;
+            }
+            let mut data = self.read_file(&path)?;
+            if name.ends_with(".gz") {
+                let mut decoded = Vec::new();
+                flate2::read::GzDecoder::new(&data[..])
+                    .read_to_end(&mut decoded)
+                    .map_err(|e| Error::InvalidFormat(format!("{}: {}", path, e)))?;
+                data = decoded;
+            } else if name.contains(".xz") || name.contains(".bz2") || name.contains(".zst") {
+                log::debug!("Skipping compressed login accounting file {}", path);
+                continue;
+            }
+            events.extend(parse_utmp(&data, name.starts_with("btmp"), &path));
+        }
+
+        events.sort_by_key(|event| event.time);
+        match_logouts(&mut events);
+        Ok(events)
+    }
+
+    /// Last login of every account in `/etc/passwd` that has one in lastlog
+    pub fn last_logins(&mut self) -> Result<Vec<LastLogin>> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: last_logins");
+        }
+
+        if !self.is_file(LASTLOG).unwrap_or(false) {
+            return Ok(Vec::new());
+        }
+        let size = self.stat(LASTLOG)?.size.max(0) as u64;
+
+        let passwd = self.cat("/etc/passwd").unwrap_or_default();
+        let mut logins = Vec::new();
+        for line in passwd.lines() {
+            let parts: Vec<&str> = line.split(':').collect();
+            let Some(uid) = parts.get(2).and_then(|uid| uid.parse::<u32>().ok()) else {
+                continue;
+            };
+            let offset = uid as u64 * LASTLOG_SIZE as u64;
+            if offset + LASTLOG_SIZE as u64 > size {
+                continue;
+            }
+            let record = self.pread(LASTLOG, LASTLOG_SIZE as i32, offset as i64)?;
+            if let Some((time, line, host)) = parse_lastlog(&record) {
+                logins.push(LastLogin {
+                    user: parts[0].to_string(),
+                    uid,
+                    time,
+                    line,
+                    host,
+                });
+            }
+        }
+        Ok(logins)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn utmp(ut_type: i16, line: &str, user: &str, host: &str, addr: &[u8], time: i32) -> Vec<u8> {
+        let mut record = vec![0u8; UTMP_SIZE];
+        record[..2].copy_from_slice(&ut_type.to_le_bytes());
+        record[4..8].copy_from_slice(&1234i32.to_le_bytes());
+        record[8..8 + line.len()].copy_from_slice(line.as_bytes());
+        record[44..44 + user.len()].copy_from_slice(user.as_bytes());
+        record[76..76 + host.len()].copy_from_slice(host.as_bytes());
+        record[340..344].copy_from_slice(&time.to_le_bytes());
+        record[348..348 + addr.len()].copy_from_slice(addr);
+        record
+    }
+
+    #[test]
+    fn test_parse_wtmp() {
+        let mut data = Vec::new();
+        data.extend(utmp(BOOT_TIME, "~", "reboot", "6.1.0-18-amd64", &[], 1000));
+        data.extend(utmp(
+            USER_PROCESS,
+            "pts/0",
+            "alice",
+            "10.0.0.5",
+            &[10, 0, 0, 5],
+            1100,
+        ));
+        data.extend(utmp(DEAD_PROCESS, "pts/0", "", "", &[], 1200));
+        data.extend(utmp(RUN_LVL, "~~", "shutdown", "6.1.0-18-amd64", &[], 1300));
+        // Torn trailing record
+        data.extend_from_slice(&[7, 0, 0]);
+
+        let mut events = parse_utmp(&data, false, "/var/log/wtmp");
+        match_logouts(&mut events);
+        let kinds: Vec<LoginKind> = events.iter().map(|e| e.kind).collect();
+        assert_eq!(
+            kinds,
+            [
+                LoginKind::Boot,
+                LoginKind::Login,
+                LoginKind::Logout,
+                LoginKind::Shutdown
+            ]
+        );
+        assert_eq!(events[1].ip, Some("10.0.0.5".parse().unwrap()));
+        assert_eq!(events[1].describe(), "alice on pts/0 from 10.0.0.5");
+        assert_eq!(events[2].user, "alice");
+        assert_eq!(events[1].remote().as_deref(), Some("10.0.0.5"));
+        assert_eq!(events[0].describe(), "system boot (6.1.0-18-amd64)");
+    }
+
+    #[test]
+    fn test_parse_btmp() {
+        let v6: Vec<u8> = "2001:db8::7".parse::<Ipv6Addr>().unwrap().octets().to_vec();
+        let data = [
+            utmp(
+                LOGIN_PROCESS,
+                "ssh:notty",
+                "root",
+                "attacker.example",
+                &v6,
+                2000,
+            ),
+            utmp(LOGIN_PROCESS, "ssh:notty", "admin", "", &[], 0),
+        ]
+        .concat();
+        let events = parse_utmp(&data, true, "/var/log/btmp");
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].kind, LoginKind::FailedLogin);
+        assert_eq!(events[0].remote().as_deref(), Some("2001:db8::7"));
+        assert_eq!(
+            events[0].describe(),
+            "root on ssh:notty from attacker.example (2001:db8::7)"
+        );
+    }
+
+    #[test]
+    fn test_parse_lastlog() {
+        let mut record = vec![0u8; LASTLOG_SIZE];
+        assert_eq!(parse_lastlog(&record), None);
+        record[..4].copy_from_slice(&1_700_000_000i32.to_le_bytes());
+        record[4..9].copy_from_slice(b"pts/1");
+        record[36..44].copy_from_slice(b"10.1.2.3");
+        assert_eq!(
+            parse_lastlog(&record),
+            Some((1_700_000_000, "pts/1".to_string(), "10.1.2.3".to_string()))
+        );
+    }
+}
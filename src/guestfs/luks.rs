@@ -3,11 +3,305 @@
 //!
 //! This implementation uses cryptsetup command-line tool.
 //!
-//! **Requires**: cryptsetup and sudo/root permissions
+//! Detection parses the LUKS1 header and the LUKS2 JSON metadata directly,
+//! including the tokens that bind key slots to tang servers or a TPM2
+//! (clevis, systemd-cryptenroll). Keys registered with
+//! [`Guestfs::add_luks_key`] before launch are tried against every LUKS
+//! volume found during launch; unlocked volumes show up in
+//! `list_filesystems` as `/dev/mapper/*` devices.
+//!
+//! **Requires**: cryptsetup and sudo/root permissions (clevis for
+//! [`LuksKey::Clevis`])
 
 use crate::core::{Error, Result};
+use crate::guestfs::handle::GuestfsState;
 use crate::guestfs::Guestfs;
+use base64::Engine;
+use serde::{Deserialize, Serialize};
+use serde_json::Value;
+use std::fmt;
+use std::io::IsTerminal;
+use std::path::{Path, PathBuf};
 use std::process::Command;
+use std::sync::RwLock;
+
+/// Magic at the start of LUKS1 and LUKS2 headers
+const LUKS_MAGIC: &[u8; 6] = b"LUKS\xba\xbe";
+
+/// Size of the LUKS1 header and of the LUKS2 binary header
+const LUKS1_HEADER_SIZE: usize = 592;
+const LUKS2_BINARY_HEADER_SIZE: u64 = 4096;
+
+/// Upper bound on the LUKS2 JSON area
+const MAX_JSON_SIZE: u64 = 4 * 1024 * 1024;
+
+/// LUKS1 key slot table
+const LUKS1_KEYSLOT_OFFSET: usize = 208;
+const LUKS1_KEYSLOT_SIZE: usize = 48;
+const LUKS1_KEYSLOTS: usize = 8;
+const LUKS1_KEY_ENABLED: u32 = 0x00AC_71F3;
+
+/// Nesting limit for clevis sss pins
+const MAX_SSS_DEPTH: usize = 4;
+
+/// Passphrase prompts per volume before giving up
+const PROMPT_ATTEMPTS: usize = 3;
+
+/// Keys that new handles start with (see [`set_default_keys`])
+static DEFAULT_KEYS: RwLock<Vec<(Option<String>, LuksKey)>> = RwLock::new(Vec::new());
+
+/// Key used to unlock a LUKS volume
+#[derive(Clone, PartialEq, Eq)]
+pub enum LuksKey {
+    /// Passphrase
+    Passphrase(String),
+    /// Key file, handed to cryptsetup as is
+    File(PathBuf),
+    /// Unlock through the clevis binding of the volume (tang, tpm2, sss)
+    Clevis,
+    /// Ask for a passphrase on the terminal
+    Prompt,
+}
+
+impl fmt::Debug for LuksKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            LuksKey::Passphrase(_) => write!(f, "Passphrase([hidden])"),
+            LuksKey::File(path) => write!(f, "File({})", path.display()),
+            LuksKey::Clevis => write!(f, "Clevis"),
+            LuksKey::Prompt => write!(f, "Prompt"),
+        }
+    }
+}
+
+/// How a key slot is unlocked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LuksBinding {
+    /// Passphrase or key file (no token references the slot)
+    Password,
+    /// Tang server (clevis)
+    Tang,
+    /// TPM2 (clevis or systemd-cryptenroll)
+    Tpm2,
+    /// FIDO2 security key (systemd-cryptenroll)
+    Fido2,
+    /// PKCS#11 token (systemd-cryptenroll)
+    Pkcs11,
+    /// Shamir secret sharing over other pins (clevis)
+    Sss,
+    /// Token type not known to guestkit
+    Other,
+}
+
+impl fmt::Display for LuksBinding {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let name = match self {
+            LuksBinding::Password => "password",
+            LuksBinding::Tang => "tang",
+            LuksBinding::Tpm2 => "tpm2",
+            LuksBinding::Fido2 => "fido2",
+            LuksBinding::Pkcs11 => "pkcs11",
+            LuksBinding::Sss => "sss",
+            LuksBinding::Other => "other",
+        };
+        f.write_str(name)
+    }
+}
+
+/// Active key slot
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LuksKeyslot {
+    pub id: u32,
+    /// Key derivation function (pbkdf2, argon2i, argon2id)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub kdf: Option<String>,
+    /// Password unless a token references the slot
+    pub binding: LuksBinding,
+}
+
+/// LUKS2 token
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LuksToken {
+    pub id: u32,
+    /// Token type as stored in the header (clevis, systemd-tpm2, ...)
+    pub token_type: String,
+    pub binding: LuksBinding,
+    /// Key slots the token unlocks
+    pub keyslots: Vec<u32>,
+    /// Pins combined by an sss binding
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub pins: Vec<LuksBinding>,
+    /// Tang servers the key is bound to
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tang_urls: Vec<String>,
+    /// TPM2 PCRs the key is sealed against
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub tpm2_pcrs: Option<String>,
+}
+
+/// LUKS volume header
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct LuksInfo {
+    /// Header version (1 or 2)
+    pub version: u16,
+    pub uuid: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub label: Option<String>,
+    /// Data cipher (e.g. "aes-xts-plain64")
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub cipher: Option<String>,
+    pub keyslots: Vec<LuksKeyslot>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub tokens: Vec<LuksToken>,
+}
+
+impl LuksInfo {
+    /// Parse the LUKS header of a device
+    ///
+    /// `read_at` reads bytes at an offset relative to the start of the
+    /// device. Returns `None` if the device does not start with a LUKS
+    /// header.
+    pub fn probe<F>(mut read_at: F) -> Result<Option<Self>>
+    where
+        F: FnMut(u64, &mut [u8]) -> Result<()>,
+    {
+        let mut header = vec![0u8; LUKS1_HEADER_SIZE];
+        read_at(0, &mut header)?;
+        if &header[..6] != LUKS_MAGIC {
+            return Ok(None);
+        }
+
+        match read_u16_be(&header, 6) {
+            Some(1) => Ok(Some(Self::parse_luks1(&header))),
+            Some(2) => {
+                let hdr_size = read_u64_be(&header, 8).unwrap_or(0);
+                if hdr_size <= LUKS2_BINARY_HEADER_SIZE
+                    || hdr_size > LUKS2_BINARY_HEADER_SIZE + MAX_JSON_SIZE
+                {
+                    return Err(Error::InvalidFormat(format!(
+                        "Invalid LUKS2 header size: {}",
+                        hdr_size
+                    )));
+                }
+
+                let mut json = vec![0u8; (hdr_size - LUKS2_BINARY_HEADER_SIZE) as usize];
+                read_at(LUKS2_BINARY_HEADER_SIZE, &mut json)?;
+                Self::parse_luks2(&header, &json).map(Some)
+            }
+            _ => Ok(None),
+        }
+    }
+
+    /// Distinct bindings of the active key slots
+    pub fn bindings(&self) -> Vec<LuksBinding> {
+        let mut bindings: Vec<LuksBinding> = self.keyslots.iter().map(|k| k.binding).collect();
+        bindings.sort();
+        bindings.dedup();
+        bindings
+    }
+
+    /// Whether a clevis token is bound to the volume
+    pub fn has_clevis(&self) -> bool {
+        self.tokens.iter().any(|t| t.token_type == "clevis")
+    }
+
+    /// Whether `key` is worth trying on this volume
+    pub fn accepts(&self, key: &LuksKey) -> bool {
+        match key {
+            LuksKey::Clevis => self.has_clevis(),
+            _ => true,
+        }
+    }
+
+    fn parse_luks1(header: &[u8]) -> Self {
+        let cipher = match (c_string(&header[8..40]), c_string(&header[40..72])) {
+            (Some(name), Some(mode)) => Some(format!("{}-{}", name, mode)),
+            (name, _) => name,
+        };
+
+        let keyslots = (0..LUKS1_KEYSLOTS)
+            .filter(|i| {
+                read_u32_be(header, LUKS1_KEYSLOT_OFFSET + i * LUKS1_KEYSLOT_SIZE)
+                    == Some(LUKS1_KEY_ENABLED)
+            })
+            .map(|i| LuksKeyslot {
+                id: i as u32,
+                kdf: Some("pbkdf2".to_string()),
+                binding: LuksBinding::Password,
+            })
+            .collect();
+
+        LuksInfo {
+            version: 1,
+            uuid: c_string(&header[168..208]).unwrap_or_default(),
+            label: None,
+            cipher,
+            keyslots,
+            tokens: Vec::new(),
+        }
+    }
+
+    fn parse_luks2(header: &[u8], json: &[u8]) -> Result<Self> {
+        let end = json.iter().position(|&b| b == 0).unwrap_or(json.len());
+        let metadata: Value = serde_json::from_slice(&json[..end])
+            .map_err(|e| Error::InvalidFormat(format!("Invalid LUKS2 metadata: {}", e)))?;
+
+        let mut keyslots: Vec<LuksKeyslot> = json_objects(&metadata, "keyslots")
+            .map(|(id, slot)| LuksKeyslot {
+                id,
+                kdf: slot["kdf"]["type"].as_str().map(str::to_string),
+                binding: LuksBinding::Password,
+            })
+            .collect();
+        keyslots.sort_by_key(|k| k.id);
+
+        let mut tokens: Vec<LuksToken> = json_objects(&metadata, "tokens")
+            .map(|(id, token)| parse_token(id, token))
+            .collect();
+        tokens.sort_by_key(|t| t.id);
+
+        for token in &tokens {
+            for slot in keyslots
+                .iter_mut()
+                .filter(|k| token.keyslots.contains(&k.id))
+            {
+                slot.binding = token.binding;
+            }
+        }
+
+        let cipher = json_objects(&metadata, "segments")
+            .min_by_key(|(id, _)| *id)
+            .and_then(|(_, segment)| segment["encryption"].as_str().map(str::to_string));
+
+        Ok(LuksInfo {
+            version: 2,
+            uuid: c_string(&header[168..208]).unwrap_or_default(),
+            label: c_string(&header[24..72]),
+            cipher,
+            keyslots,
+            tokens,
+        })
+    }
+}
+
+/// Set the keys that handles created afterwards start with
+///
+/// Meant for front ends that take keys once (e.g. on the command line) and
+/// create handles in many places. Keys added with
+/// [`Guestfs::add_luks_key`] are tried after these.
+pub fn set_default_keys(keys: Vec<(Option<String>, LuksKey)>) {
+    let mut defaults = DEFAULT_KEYS.write().unwrap_or_else(|e| e.into_inner());
+    *defaults = keys;
+}
+
+/// Keys that new handles start with
+pub(crate) fn default_keys() -> Vec<(Option<String>, LuksKey)> {
+    DEFAULT_KEYS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
 
 impl Guestfs {
     /// Open a LUKS encrypted device
@@ -413,6 +707,448 @@ impl Guestfs {
         let uuid = String::from_utf8_lossy(&output.stdout).trim().to_string();
         Ok(uuid)
     }
+
+    /// Register a key for LUKS volumes
+    ///
+    /// Must be called before launch. `selector` limits the key to one
+    /// volume, given by device (e.g. "/dev/sda2") or LUKS UUID; without it
+    /// the key is tried on every LUKS volume. Volumes that a key was given
+    /// for but none of the keys unlock fail the launch, except when the
+    /// passphrase prompt is left empty.
+    ///
+    /// # Examples
+    ///
+    /// ```no_run
+    /// use guestkit::guestfs::Guestfs;
+    /// use guestkit::guestfs::luks::LuksKey;
+    ///
+    /// # fn main() -> Result<(), Box<dyn std::error::Error>> {
+    /// let mut g = Guestfs::new()?;
+    /// g.add_drive_ro("/path/to/encrypted.qcow2")?;
+    /// g.add_luks_key(Some("/dev/sda2"), LuksKey::File("/root/sda2.key".into()))?;
+    /// g.add_luks_key(None, LuksKey::Clevis)?;
+    /// g.launch()?;
+    ///
+    /// // The unlocked volume is listed as /dev/mapper/luks-<pid>-sda2
+    /// for (device, fstype) in g.list_filesystems()? {
+    ///     println!("{} {}", device, fstype);
+    /// }
+    /// # Ok(())
+    /// # }
+    /// ```
+    pub fn add_luks_key(&mut self, selector: Option<&str>, key: LuksKey) -> Result<()> {
+        if self.state != GuestfsState::Config {
+            return Err(Error::InvalidState(
+                "Cannot add keys after launch".to_string(),
+            ));
+        }
+
+        self.luks_keys.push((selector.map(|s| s.to_string()), key));
+        Ok(())
+    }
+
+    /// Detect LUKS encryption on a device
+    ///
+    /// Returns the header with key slots and tokens, or `None` if the
+    /// device is not LUKS encrypted.
+    pub(crate) fn luks_detect_untraced(&mut self, device: &str) -> Result<Option<LuksInfo>> {
+        self.ensure_ready()?;
+
+        let offset = self.partition_offset(device)?;
+        let reader = self
+            .reader
+            .as_mut()
+            .ok_or_else(|| Error::InvalidState("Reader not initialized".to_string()))?;
+        LuksInfo::probe(|pos, buf| reader.read_exact_at(offset + pos, buf))
+    }
+
+    /// Mapped device of a LUKS volume unlocked at launch
+    ///
+    /// Returns e.g. "/dev/mapper/luks-1234-sda2", or `None` if the volume
+    /// is locked.
+    pub fn luks_mapping(&self, device: &str) -> Option<String> {
+        self.luks_volumes
+            .get(device)
+            .map(|mapname| format!("/dev/mapper/{}", mapname))
+    }
+
+    /// Unlock every LUKS volume that a registered key applies to
+    pub(crate) fn unlock_luks_volumes(&mut self) -> Result<()> {
+        let partitions: Vec<u32> = self
+            .partition_table()?
+            .partitions()
+            .iter()
+            .map(|p| p.number)
+            .collect();
+        let readonly = self.drives.first().is_some_and(|d| d.readonly);
+
+        for number in partitions {
+            let device = format!("/dev/sda{}", number);
+            let Some(info) = self.luks_detect(&device)? else {
+                continue;
+            };
+
+            let keys: Vec<LuksKey> = self
+                .luks_keys
+                .iter()
+                .filter(|(selector, key)| {
+                    selector
+                        .as_deref()
+                        .is_none_or(|s| s == device || s == info.uuid)
+                        && info.accepts(key)
+                })
+                .map(|(_, key)| key.clone())
+                .collect();
+            if keys.is_empty() {
+                if self.verbose {
+                    eprintln!("guestfs: no key for LUKS volume {}", device);
+                }
+                continue;
+            }
+
+            let mapname = format!("luks-{}-sda{}", std::process::id(), number);
+            let mut errors = Vec::new();
+            for key in &keys {
+                match self.luks_unlock(&device, &info, key, &mapname, readonly) {
+                    Ok(true) => {
+                        errors.clear();
+                        break;
+                    }
+                    Ok(false) => {}
+                    Err(e) => errors.push(e.to_string()),
+                }
+            }
+            if !errors.is_empty() {
+                return Err(Error::CommandFailed(format!(
+                    "Could not unlock LUKS volume {} with the {} key(s) given: {}",
+                    device,
+                    keys.len(),
+                    errors.join("; ")
+                )));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Close all LUKS mappings opened at launch
+    pub(crate) fn close_luks_volumes(&mut self) {
+        let mapnames: Vec<String> = self.luks_volumes.values().cloned().collect();
+        for mapname in mapnames {
+            if self.trace {
+                eprintln!("guestfs: closing LUKS mapping {}", mapname);
+            }
+
+            match Command::new("cryptsetup")
+                .arg("close")
+                .arg(&mapname)
+                .output()
+            {
+                Ok(out) if out.status.success() => {}
+                Ok(out) => {
+                    eprintln!(
+                        "Warning: failed to close LUKS mapping {}: {}",
+                        mapname,
+                        String::from_utf8_lossy(&out.stderr)
+                    );
+                }
+                Err(e) => {
+                    eprintln!("Warning: failed to run cryptsetup for {}: {}", mapname, e);
+                }
+            }
+        }
+        self.luks_volumes.clear();
+    }
+
+    /// Try one key on a volume; false if the key was not available
+    /// (prompt left empty or no terminal)
+    fn luks_unlock(
+        &mut self,
+        device: &str,
+        info: &LuksInfo,
+        key: &LuksKey,
+        mapname: &str,
+        readonly: bool,
+    ) -> Result<bool> {
+        if self.verbose {
+            eprintln!("guestfs: unlocking LUKS volume {} [{:?}]", device, key);
+        }
+
+        let host_device = self.host_device_path(device)?;
+        match key {
+            LuksKey::Passphrase(passphrase) => cryptsetup_open(
+                &host_device,
+                mapname,
+                readonly,
+                KeySource::Stdin(passphrase.as_bytes()),
+            )?,
+            LuksKey::File(path) => {
+                cryptsetup_open(&host_device, mapname, readonly, KeySource::File(path))?
+            }
+            LuksKey::Clevis => clevis_unlock(&host_device, mapname, readonly)?,
+            LuksKey::Prompt => {
+                if !std::io::stdin().is_terminal() {
+                    if self.verbose {
+                        eprintln!("guestfs: no terminal to prompt for {}", device);
+                    }
+                    return Ok(false);
+                }
+
+                let prompt = format!("Enter passphrase for {} ({}): ", device, info.uuid);
+                let mut attempt = 1;
+                loop {
+                    let passphrase = rpassword::prompt_password(&prompt).map_err(|e| {
+                        Error::CommandFailed(format!("Failed to read passphrase: {}", e))
+                    })?;
+                    if passphrase.is_empty() {
+                        return Ok(false);
+                    }
+
+                    match cryptsetup_open(
+                        &host_device,
+                        mapname,
+                        readonly,
+                        KeySource::Stdin(passphrase.as_bytes()),
+                    ) {
+                        Ok(()) => break,
+                        Err(e) if attempt >= PROMPT_ATTEMPTS => return Err(e),
+                        Err(_) => {
+                            eprintln!("No key available with this passphrase.");
+                            attempt += 1;
+                        }
+                    }
+                }
+            }
+        }
+
+        self.luks_volumes
+            .insert(device.to_string(), mapname.to_string());
+
+        if self.verbose {
+            eprintln!("guestfs: LUKS device opened as /dev/mapper/{}", mapname);
+        }
+
+        Ok(true)
+    }
+}
+
+/// Where cryptsetup reads the key from
+enum KeySource<'a> {
+    Stdin(&'a [u8]),
+    File(&'a Path),
+}
+
+/// Open a LUKS volume on a host block device
+fn cryptsetup_open(
+    host_device: &Path,
+    mapname: &str,
+    readonly: bool,
+    key: KeySource<'_>,
+) -> Result<()> {
+    let mut cmd = Command::new("cryptsetup");
+    cmd.arg("open")
+        .arg(host_device)
+        .arg(mapname)
+        .arg("--type")
+        .arg("luks");
+    if readonly {
+        cmd.arg("--readonly");
+    }
+    if let KeySource::File(path) = key {
+        cmd.arg("--key-file").arg(path);
+    }
+
+    let mut child = cmd
+        .stdin(std::process::Stdio::piped())
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::piped())
+        .spawn()
+        .map_err(|e| {
+            Error::CommandFailed(format!(
+                "Failed to run cryptsetup: {}. Is cryptsetup installed? Requires sudo/root.",
+                e
+            ))
+        })?;
+
+    if let Some(mut stdin) = child.stdin.take() {
+        if let KeySource::Stdin(passphrase) = key {
+            use std::io::Write;
+            stdin
+                .write_all(passphrase)
+                .map_err(|e| Error::CommandFailed(format!("Failed to write key: {}", e)))?;
+        }
+    }
+
+    let output = child
+        .wait_with_output()
+        .map_err(|e| Error::CommandFailed(format!("Failed to wait for cryptsetup: {}", e)))?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::CommandFailed(format!(
+            "LUKS open failed: {}. Check passphrase and device.",
+            stderr.trim()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Open a LUKS volume through its clevis binding
+fn clevis_unlock(host_device: &Path, mapname: &str, readonly: bool) -> Result<()> {
+    let mut cmd = Command::new("clevis");
+    cmd.arg("luks")
+        .arg("unlock")
+        .arg("-d")
+        .arg(host_device)
+        .arg("-n")
+        .arg(mapname);
+    if readonly {
+        cmd.arg("-o").arg("--readonly");
+    }
+
+    let output = cmd.output().map_err(|e| {
+        Error::CommandFailed(format!(
+            "Failed to run clevis: {}. Is clevis-luks installed?",
+            e
+        ))
+    })?;
+
+    if !output.status.success() {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        return Err(Error::CommandFailed(format!(
+            "clevis unlock failed: {}",
+            stderr.trim()
+        )));
+    }
+
+    Ok(())
+}
+
+/// Entries of a LUKS2 metadata object keyed by numeric id
+fn json_objects<'a>(metadata: &'a Value, section: &str) -> impl Iterator<Item = (u32, &'a Value)> {
+    metadata[section]
+        .as_object()
+        .into_iter()
+        .flatten()
+        .filter_map(|(id, value)| Some((id.parse().ok()?, value)))
+}
+
+fn parse_token(id: u32, token: &Value) -> LuksToken {
+    let mut parsed = LuksToken {
+        id,
+        token_type: token["type"].as_str().unwrap_or("unknown").to_string(),
+        binding: LuksBinding::Other,
+        keyslots: token["keyslots"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter_map(|slot| match slot {
+                Value::String(s) => s.parse().ok(),
+                other => other.as_u64().map(|n| n as u32),
+            })
+            .collect(),
+        pins: Vec::new(),
+        tang_urls: Vec::new(),
+        tpm2_pcrs: None,
+    };
+
+    parsed.binding = match parsed.token_type.as_str() {
+        "clevis" => token["jwe"]["protected"]
+            .as_str()
+            .and_then(clevis_config)
+            .map(|clevis| apply_clevis_pin(&mut parsed, &clevis, 0))
+            .unwrap_or(LuksBinding::Other),
+        "systemd-tpm2" => {
+            parsed.tpm2_pcrs = token["tpm2-pcrs"].as_array().map(|pcrs| {
+                pcrs.iter()
+                    .filter_map(Value::as_u64)
+                    .map(|pcr| pcr.to_string())
+                    .collect::<Vec<_>>()
+                    .join(",")
+            });
+            LuksBinding::Tpm2
+        }
+        "systemd-fido2" => LuksBinding::Fido2,
+        "systemd-pkcs11" => LuksBinding::Pkcs11,
+        _ => LuksBinding::Other,
+    };
+    parsed
+}
+
+/// Clevis configuration from a base64url JWE protected header
+fn clevis_config(protected: &str) -> Option<Value> {
+    let decoded = base64::engine::general_purpose::URL_SAFE_NO_PAD
+        .decode(protected.trim_end_matches('='))
+        .ok()?;
+    let header: Value = serde_json::from_slice(&decoded).ok()?;
+    header.get("clevis").cloned()
+}
+
+/// Binding of a clevis pin, collecting tang URLs, PCRs and sss pins
+fn apply_clevis_pin(token: &mut LuksToken, clevis: &Value, depth: usize) -> LuksBinding {
+    match clevis["pin"].as_str() {
+        Some("tang") => {
+            if let Some(url) = clevis["tang"]["url"].as_str() {
+                token.tang_urls.push(url.to_string());
+            }
+            LuksBinding::Tang
+        }
+        Some("tpm2") => {
+            token.tpm2_pcrs = match &clevis["tpm2"]["pcr_ids"] {
+                Value::String(ids) => Some(ids.clone()),
+                Value::Array(ids) => Some(
+                    ids.iter()
+                        .map(|id| id.to_string())
+                        .collect::<Vec<_>>()
+                        .join(","),
+                ),
+                _ => None,
+            };
+            LuksBinding::Tpm2
+        }
+        Some("sss") if depth < MAX_SSS_DEPTH => {
+            // Each share is a compact JWE whose first part is its header
+            let shares: Vec<Value> = clevis["sss"]["jwe"]
+                .as_array()
+                .into_iter()
+                .flatten()
+                .filter_map(Value::as_str)
+                .filter_map(|jwe| clevis_config(jwe.split('.').next()?))
+                .collect();
+            for share in &shares {
+                let pin = apply_clevis_pin(token, share, depth + 1);
+                if !token.pins.contains(&pin) {
+                    token.pins.push(pin);
+                }
+            }
+            LuksBinding::Sss
+        }
+        _ => LuksBinding::Other,
+    }
+}
+
+/// NUL-terminated string from a fixed-size header field
+fn c_string(field: &[u8]) -> Option<String> {
+    let end = field.iter().position(|&b| b == 0).unwrap_or(field.len());
+    let value = String::from_utf8_lossy(&field[..end]).trim().to_string();
+    (!value.is_empty()).then_some(value)
+}
+
+fn read_u16_be(data: &[u8], offset: usize) -> Option<u16> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_be_bytes([b[0], b[1]]))
+}
+
+fn read_u32_be(data: &[u8], offset: usize) -> Option<u32> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+}
+
+fn read_u64_be(data: &[u8], offset: usize) -> Option<u64> {
+    data.get(offset..offset + 8)
+        .and_then(|b| b.try_into().ok())
+        .map(u64::from_be_bytes)
 }
 
 #[cfg(test)]
@@ -425,4 +1161,125 @@ mod tests {
         // API structure test
         let _ = g;
     }
+
+    const UUID: &str = "0a3f4c2e-5b6d-4e8f-9a1b-2c3d4e5f6a7b";
+
+    fn protected(header: serde_json::Value) -> String {
+        base64::engine::general_purpose::URL_SAFE_NO_PAD.encode(header.to_string())
+    }
+
+    fn luks2(metadata: serde_json::Value) -> Vec<u8> {
+        let mut data = vec![0u8; 16384];
+        data[..6].copy_from_slice(LUKS_MAGIC);
+        data[6..8].copy_from_slice(&2u16.to_be_bytes());
+        data[8..16].copy_from_slice(&16384u64.to_be_bytes());
+        data[24..28].copy_from_slice(b"root");
+        data[168..168 + UUID.len()].copy_from_slice(UUID.as_bytes());
+        let json = metadata.to_string();
+        data[4096..4096 + json.len()].copy_from_slice(json.as_bytes());
+        data
+    }
+
+    fn probe(data: &[u8]) -> Option<LuksInfo> {
+        LuksInfo::probe(|pos, buf| {
+            let start = pos as usize;
+            buf.copy_from_slice(&data[start..start + buf.len()]);
+            Ok(())
+        })
+        .unwrap()
+    }
+
+    #[test]
+    fn test_probe_luks2_tokens() {
+        let tang = protected(serde_json::json!({
+            "alg": "ECDH-ES",
+            "clevis": {"pin": "tang", "tang": {"url": "http://tang.example.com"}}
+        }));
+        let tpm2 = protected(serde_json::json!({
+            "clevis": {"pin": "tpm2", "tpm2": {"hash": "sha256", "pcr_ids": "7"}}
+        }));
+        let sss = protected(serde_json::json!({
+            "clevis": {"pin": "sss", "sss": {"t": 1, "jwe": [
+                format!("{}..iv.ct.tag", tang),
+                format!("{}..iv.ct.tag", tpm2),
+            ]}}
+        }));
+        let data = luks2(serde_json::json!({
+            "keyslots": {
+                "0": {"type": "luks2", "kdf": {"type": "argon2id"}},
+                "1": {"type": "luks2", "kdf": {"type": "pbkdf2"}},
+                "2": {"type": "luks2", "kdf": {"type": "pbkdf2"}},
+            },
+            "tokens": {
+                "0": {"type": "clevis", "keyslots": ["1"], "jwe": {"protected": sss}},
+                "1": {"type": "systemd-tpm2", "keyslots": ["2"], "tpm2-pcrs": [0, 7]},
+            },
+            "segments": {"0": {"type": "crypt", "encryption": "aes-xts-plain64"}},
+        }));
+
+        let info = probe(&data).unwrap();
+        assert_eq!(info.version, 2);
+        assert_eq!(info.uuid, UUID);
+        assert_eq!(info.label.as_deref(), Some("root"));
+        assert_eq!(info.cipher.as_deref(), Some("aes-xts-plain64"));
+
+        let bindings: Vec<_> = info.keyslots.iter().map(|k| (k.id, k.binding)).collect();
+        assert_eq!(
+            bindings,
+            vec![
+                (0, LuksBinding::Password),
+                (1, LuksBinding::Sss),
+                (2, LuksBinding::Tpm2),
+            ]
+        );
+        assert_eq!(info.keyslots[0].kdf.as_deref(), Some("argon2id"));
+
+        let clevis = &info.tokens[0];
+        assert_eq!(clevis.pins, vec![LuksBinding::Tang, LuksBinding::Tpm2]);
+        assert_eq!(clevis.tang_urls, vec!["http://tang.example.com"]);
+        assert_eq!(clevis.tpm2_pcrs.as_deref(), Some("7"));
+        assert_eq!(info.tokens[1].tpm2_pcrs.as_deref(), Some("0,7"));
+
+        assert!(info.has_clevis());
+        assert!(info.accepts(&LuksKey::Clevis));
+        assert_eq!(
+            info.bindings(),
+            vec![LuksBinding::Password, LuksBinding::Tpm2, LuksBinding::Sss]
+        );
+    }
+
+    #[test]
+    fn test_probe_luks1() {
+        let mut data = vec![0u8; LUKS1_HEADER_SIZE];
+        data[..6].copy_from_slice(LUKS_MAGIC);
+        data[6..8].copy_from_slice(&1u16.to_be_bytes());
+        data[8..11].copy_from_slice(b"aes");
+        data[40..51].copy_from_slice(b"xts-plain64");
+        data[168..168 + UUID.len()].copy_from_slice(UUID.as_bytes());
+        for slot in [0, 3] {
+            let offset = LUKS1_KEYSLOT_OFFSET + slot * LUKS1_KEYSLOT_SIZE;
+            data[offset..offset + 4].copy_from_slice(&LUKS1_KEY_ENABLED.to_be_bytes());
+        }
+
+        let info = probe(&data).unwrap();
+        assert_eq!(info.version, 1);
+        assert_eq!(info.cipher.as_deref(), Some("aes-xts-plain64"));
+        assert_eq!(
+            info.keyslots.iter().map(|k| k.id).collect::<Vec<_>>(),
+            vec![0, 3]
+        );
+        assert!(!info.accepts(&LuksKey::Clevis));
+        assert_eq!(info.bindings(), vec![LuksBinding::Password]);
+    }
+
+    #[test]
+    fn test_probe_non_luks() {
+        assert!(probe(&[0u8; 4096]).is_none());
+    }
+
+    #[test]
+    fn test_key_debug_hides_passphrase() {
+        let key = LuksKey::Passphrase("hunter2".to_string());
+        assert!(!format!("{:?}", key).contains("hunter2"));
+    }
 }
@@ -3,12 +3,25 @@
 //!
 //! This implementation uses LVM command-line tools (lvm2 package).
 //!
+//! Besides linear LVs, guests commonly use thin-provisioned LVs (the RHEL
+//! installer default), cached LVs and RAID LVs. Activation brings all of
+//! them up; thin and cache pools only hold other LVs' data and are left
+//! out of [`Guestfs::lvs`]. Thin and cache pools are checked with
+//! `thin_check`/`cache_check` (device-mapper-persistent-data) when
+//! installed.
+//!
 //! **Requires**: lvm2 package and sudo/root permissions
 
 use crate::core::{Error, Result};
 use crate::guestfs::Guestfs;
+use serde::{Deserialize, Serialize};
+use std::path::Path;
 use std::process::Command;
 
+/// Where LVM looks for the pool metadata checkers
+const THIN_CHECK: &str = "/usr/sbin/thin_check";
+const CACHE_CHECK: &str = "/usr/sbin/cache_check";
+
 /// Logical volume information
 #[derive(Debug, Clone)]
 pub struct LV {
@@ -30,26 +43,203 @@ pub struct LV {
     pub modules: String,
 }
 
-impl Guestfs {
-    /// Get device filter config for LVM to restrict to NBD/loop devices only
-    fn get_lvm_device_filter(&self) -> String {
-        let device_path = if let Some(nbd) = &self.nbd_device {
-            nbd.device_path().display().to_string()
-        } else if let Some(loop_dev) = &self.loop_device {
-            loop_dev.device_path()
-                .map(|p| p.display().to_string())
-                .unwrap_or_else(|| "/dev/loop".to_string())
-        } else {
-            "/dev/nbd".to_string()
-        };
+/// Kind of logical volume, from its segment type
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum LvType {
+    Linear,
+    Striped,
+    Snapshot,
+    /// Thin-provisioned LV, allocated from a thin pool
+    Thin,
+    ThinPool,
+    /// LV cached by a faster cache pool or cache volume
+    Cache,
+    Writecache,
+    CachePool,
+    Raid1,
+    Raid5,
+    /// Other RAID levels (raid0, raid4, raid6, raid10)
+    Raid(String),
+    Mirror,
+    Vdo,
+    VdoPool,
+    Other(String),
+}
+
+impl LvType {
+    /// Parse an `lvs -o segtype` value
+    pub fn from_segtype(segtype: &str) -> Self {
+        match segtype {
+            "linear" => LvType::Linear,
+            "striped" => LvType::Striped,
+            "snapshot" => LvType::Snapshot,
+            "thin" => LvType::Thin,
+            "thin-pool" => LvType::ThinPool,
+            "cache" => LvType::Cache,
+            "writecache" => LvType::Writecache,
+            "cache-pool" => LvType::CachePool,
+            "raid1" => LvType::Raid1,
+            // raid5 defaults to the left-symmetric layout
+            "raid5" | "raid5_ls" | "raid5_la" | "raid5_rs" | "raid5_ra" | "raid5_n" => {
+                LvType::Raid5
+            }
+            "mirror" => LvType::Mirror,
+            "vdo" => LvType::Vdo,
+            "vdo-pool" => LvType::VdoPool,
+            other if other.starts_with("raid") => LvType::Raid(other.to_string()),
+            other => LvType::Other(other.to_string()),
+        }
+    }
 
-        // LVM filter to ONLY scan our device and reject all others
+    /// Pools hold data for other LVs rather than a filesystem
+    pub fn is_pool(&self) -> bool {
+        matches!(self, LvType::ThinPool | LvType::CachePool | LvType::VdoPool)
+    }
+}
+
+impl std::fmt::Display for LvType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            LvType::Linear => "linear",
+            LvType::Striped => "striped",
+            LvType::Snapshot => "snapshot",
+            LvType::Thin => "thin",
+            LvType::ThinPool => "thin-pool",
+            LvType::Cache => "cache",
+            LvType::Writecache => "writecache",
+            LvType::CachePool => "cache-pool",
+            LvType::Raid1 => "raid1",
+            LvType::Raid5 => "raid5",
+            LvType::Mirror => "mirror",
+            LvType::Vdo => "vdo",
+            LvType::VdoPool => "vdo-pool",
+            LvType::Raid(segtype) | LvType::Other(segtype) => segtype,
+        })
+    }
+}
+
+/// Logical volume with its layout
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct LvInfo {
+    pub vg_name: String,
+    pub lv_name: String,
+    /// Device path (`/dev/VG/LV`)
+    pub path: String,
+    pub lv_type: LvType,
+    /// Size in bytes
+    pub size: u64,
+    /// Thin pool of a thin LV, or cache pool of a cached LV
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pool: Option<String>,
+    /// Origin of a snapshot or thin snapshot
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub origin: Option<String>,
+    /// Has a device node
+    pub active: bool,
+    /// Skipped by plain `vgchange -ay` (thin snapshots by default)
+    pub activation_skip: bool,
+}
+
+/// Parse `lvs --separator '|' -o vg_name,lv_name,lv_path,segtype,lv_size,pool_lv,origin,lv_attr`
+fn parse_lvs_report(output: &str) -> Vec<LvInfo> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.trim().split('|').map(str::trim).collect();
+            let [vg_name, lv_name, path, segtype, size, pool, origin, attr] = fields[..] else {
+                return None;
+            };
+            let optional = |value: &str| (!value.is_empty()).then(|| value.to_string());
+            let attr = attr.as_bytes();
+
+            Some(LvInfo {
+                vg_name: vg_name.to_string(),
+                lv_name: lv_name.to_string(),
+                path: path.to_string(),
+                lv_type: LvType::from_segtype(segtype),
+                size: size.trim_end_matches('B').parse().unwrap_or(0),
+                pool: optional(pool),
+                origin: optional(origin),
+                active: attr.get(4) == Some(&b'a'),
+                activation_skip: attr.get(9) == Some(&b'k'),
+            })
+        })
+        .collect()
+}
+
+/// `--config` for activating the guest's volume groups
+///
+/// LVs of `read_only_vgs` are activated read-only: thin and cache pools
+/// write their metadata on activation otherwise, which fails on read-only
+/// drives. Pool metadata checks are skipped when the checker isn't
+/// installed, rather than failing the activation.
+fn activation_config(
+    devices: &str,
+    read_only_vgs: &[String],
+    thin_check: bool,
+    cache_check: bool,
+) -> String {
+    let mut global = String::from("locking_type=0");
+    if !thin_check {
+        global.push_str(" thin_check_executable=\"\"");
+    }
+    if !cache_check {
+        global.push_str(" cache_check_executable=\"\"");
+    }
+
+    let mut config = format!("{} global {{ {} }}", devices, global);
+    if !read_only_vgs.is_empty() {
+        let volumes: Vec<String> = read_only_vgs
+            .iter()
+            .map(|vg| format!("\"{}\"", vg))
+            .collect();
+        config.push_str(&format!(
+            " activation {{ read_only_volume_list=[{}] }}",
+            volumes.join(",")
+        ));
+    }
+    config
+}
+
+impl Guestfs {
+    /// `devices` section restricting LVM to our NBD/loop devices and md arrays
+    fn get_lvm_devices_section(&self) -> String {
+        // LVM filter to ONLY scan our devices and reject all others
         // This prevents accidentally discovering host LVM volumes
         // Escape forward slashes for the regex pattern
-        let escaped_path = device_path.replace("/", r"\/");
+        let mut accept: Vec<String> = self
+            .drive_devices()
+            .iter()
+            .map(|path| {
+                format!(
+                    r#""a|^{}|""#,
+                    path.display().to_string().replace("/", r"\/")
+                )
+            })
+            .collect();
+        if accept.is_empty() {
+            accept.push(r#""a|^\/dev\/nbd|""#.to_string());
+        }
+
+        // LVM sees arrays by their kernel name, /dev/md127 and its partitions
+        for md in &self.md_arrays {
+            if let Ok(path) = std::fs::canonicalize(md) {
+                accept.push(format!(
+                    r#""a|^{}(p[0-9]+)?$|""#,
+                    path.display().to_string().replace("/", r"\/")
+                ));
+            }
+        }
+
+        format!(r#"devices {{ filter=[{},"r|.*|"] }}"#, accept.join(","))
+    }
+
+    /// Get device filter config for LVM to restrict to NBD/loop devices only
+    fn get_lvm_device_filter(&self) -> String {
         format!(
-            r#"devices {{ filter=["a|^{}|","r|.*|"] }} global {{ locking_type=0 }}"#,
-            escaped_path
+            "{} global {{ locking_type=0 }}",
+            self.get_lvm_devices_section()
         )
     }
 
@@ -153,8 +343,23 @@ impl Guestfs {
         // Check if we need sudo
         let need_sudo = unsafe { libc::geteuid() } != 0;
 
-        // Get device filter to restrict LVM to our device only
-        let lvm_filter = self.get_lvm_device_filter();
+        // Get device filter to restrict LVM to our device only; read-only
+        // drives get read-only LVs so thin and cache pools can activate
+        let lvm_filter = if activate {
+            let read_only_vgs = if self.drives.iter().any(|drive| drive.readonly) {
+                self.vgs().unwrap_or_default()
+            } else {
+                Vec::new()
+            };
+            activation_config(
+                &self.get_lvm_devices_section(),
+                &read_only_vgs,
+                Path::new(THIN_CHECK).exists(),
+                Path::new(CACHE_CHECK).exists(),
+            )
+        } else {
+            self.get_lvm_device_filter()
+        };
 
         // Build vgchange command with isolation
         let mut cmd = if need_sudo {
@@ -172,9 +377,12 @@ impl Guestfs {
         };
 
         // Run vgchange to activate/deactivate all VGs
+        cmd.arg("-a").arg(action);
+        if activate {
+            // Thin snapshots are flagged to be skipped by autoactivation
+            cmd.arg("--ignoreactivationskip");
+        }
         let output = cmd
-            .arg("-a")
-            .arg(action)
             .arg("--config")
             .arg(&lvm_filter)
             .output()
@@ -230,9 +438,11 @@ impl Guestfs {
                 Command::new("vgchange")
             };
 
+            cmd.arg("-a").arg(action);
+            if activate {
+                cmd.arg("--ignoreactivationskip");
+            }
             let output = cmd
-                .arg("-a")
-                .arg(action)
                 .arg(vg)
                 .output()
                 .map_err(|e| Error::CommandFailed(format!("Failed to run vgchange: {}", e)))?;
@@ -421,8 +631,10 @@ impl Guestfs {
         // List logical volumes with device filter
         let output = cmd
             .arg("--noheadings")
+            .arg("--separator")
+            .arg("|")
             .arg("-o")
-            .arg("lv_path")
+            .arg("lv_path,segtype")
             .arg("--config")
             .arg(&lvm_filter)
             .output()
@@ -433,16 +645,60 @@ impl Guestfs {
             return Ok(Vec::new());
         }
 
+        // Pools have no filesystem of their own
         let stdout = String::from_utf8_lossy(&output.stdout);
         let lvs: Vec<String> = stdout
             .lines()
-            .map(|line| line.trim().to_string())
-            .filter(|line| !line.is_empty())
+            .filter_map(|line| line.trim().split_once('|'))
+            .filter(|(_, segtype)| !LvType::from_segtype(segtype.trim()).is_pool())
+            .map(|(path, _)| path.trim().to_string())
+            .filter(|path| !path.is_empty())
             .collect();
 
         Ok(lvs)
     }
 
+    /// List logical volumes with their type, pool and activation state
+    ///
+    /// Includes thin and cache pools, unlike [`Guestfs::lvs`]; the LVs
+    /// inside pools and RAID LVs (data, metadata, legs) are left out.
+    pub fn logical_volumes(&self) -> Result<Vec<LvInfo>> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: logical_volumes");
+        }
+
+        let lvm_filter = self.get_lvm_device_filter();
+        let mut cmd = if unsafe { libc::geteuid() } != 0 {
+            let mut sudo_cmd = Command::new("sudo");
+            sudo_cmd.arg("lvs");
+            sudo_cmd
+        } else {
+            Command::new("lvs")
+        };
+
+        let output = cmd
+            .arg("--noheadings")
+            .arg("--separator")
+            .arg("|")
+            .arg("--units")
+            .arg("b")
+            .arg("-o")
+            .arg("vg_name,lv_name,lv_path,segtype,lv_size,pool_lv,origin,lv_attr")
+            .arg("--config")
+            .arg(&lvm_filter)
+            .output()
+            .map_err(|e| Error::CommandFailed(format!("Failed to run lvs: {}", e)))?;
+
+        if !output.status.success() {
+            // Not an error if no LVs found
+            return Ok(Vec::new());
+        }
+
+        Ok(parse_lvs_report(&String::from_utf8_lossy(&output.stdout)))
+    }
+
     /// List volume groups
     ///
     pub fn vgs(&self) -> Result<Vec<String>> {
@@ -551,4 +807,56 @@ mod tests {
         let g = Guestfs::new().unwrap();
         // API structure tests
     }
+
+    #[test]
+    fn test_lv_type_from_segtype() {
+        assert_eq!(LvType::from_segtype("linear"), LvType::Linear);
+        assert_eq!(LvType::from_segtype("thin"), LvType::Thin);
+        assert_eq!(LvType::from_segtype("raid5_ls"), LvType::Raid5);
+        assert_eq!(
+            LvType::from_segtype("raid10"),
+            LvType::Raid("raid10".to_string())
+        );
+        assert!(LvType::from_segtype("thin-pool").is_pool());
+        assert!(LvType::from_segtype("cache-pool").is_pool());
+        assert!(!LvType::from_segtype("cache").is_pool());
+    }
+
+    #[test]
+    fn test_parse_lvs_report() {
+        let output = "  rhel|pool00||thin-pool|18249416704B|||twi-aotz--\n  \
+                      rhel|root|/dev/rhel/root|thin|15502147584B|pool00||Vwi-aotz--\n  \
+                      rhel|pre-upgrade|/dev/rhel/pre-upgrade|thin|15502147584B|pool00|root|Vwi---tz-k\n  \
+                      data|mirror|/dev/data/mirror|raid1|1073741824B|||rwi-a-r---\n  \
+                      data|fast|/dev/data/fast|cache|2147483648B|[fast_cpool]||Cwi-a-C---\n";
+        let lvs = parse_lvs_report(output);
+        assert_eq!(lvs.len(), 5);
+
+        assert_eq!(lvs[0].lv_type, LvType::ThinPool);
+        assert_eq!(lvs[0].path, "");
+        assert_eq!(lvs[1].pool.as_deref(), Some("pool00"));
+        assert_eq!(lvs[1].size, 15502147584);
+        assert!(lvs[1].active && !lvs[1].activation_skip);
+        assert_eq!(lvs[2].origin.as_deref(), Some("root"));
+        assert!(!lvs[2].active && lvs[2].activation_skip);
+        assert_eq!(lvs[3].lv_type, LvType::Raid1);
+        assert_eq!(lvs[4].lv_type, LvType::Cache);
+    }
+
+    #[test]
+    fn test_activation_config() {
+        let devices = r#"devices { filter=["a|^\/dev\/nbd0|","r|.*|"] }"#;
+        assert_eq!(
+            activation_config(devices, &[], true, true),
+            format!("{} global {{ locking_type=0 }}", devices)
+        );
+        assert_eq!(
+            activation_config(devices, &["rhel".to_string()], false, true),
+            format!(
+                "{} global {{ locking_type=0 thin_check_executable=\"\" }} \
+                 activation {{ read_only_volume_list=[\"rhel\"] }}",
+                devices
+            )
+        );
+    }
 }
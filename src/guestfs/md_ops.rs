@@ -4,10 +4,135 @@
 //! This implementation provides software RAID management functionality.
 
 use crate::core::{Error, Result};
+use crate::guestfs::device::sysfs_partitions;
 use crate::guestfs::Guestfs;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
+/// `/dev/md/` path of an array given by name or full path
+fn md_path(md: &str) -> String {
+    if md.starts_with("/dev/") {
+        md.to_string()
+    } else {
+        format!("/dev/md/{}", md)
+    }
+}
+
+/// Array UUIDs in `mdadm --examine --brief` output, once each
+fn parse_examine_uuids(output: &str) -> Vec<String> {
+    let mut uuids: Vec<String> = Vec::new();
+    for line in output.lines().filter(|line| line.starts_with("ARRAY")) {
+        let uuid = line
+            .split_whitespace()
+            .find_map(|field| field.strip_prefix("UUID="));
+        if let Some(uuid) = uuid {
+            if !uuids.iter().any(|known| known == uuid) {
+                uuids.push(uuid.to_string());
+            }
+        }
+    }
+    uuids
+}
+
+/// A disk and its partitions, as the kernel lists them in sysfs
+fn with_partitions(disk: &Path) -> Vec<PathBuf> {
+    let mut devices = vec![disk.to_path_buf()];
+    devices.extend(sysfs_partitions(disk).into_iter().map(|(_, path)| path));
+    devices
+}
+
 impl Guestfs {
+    /// Assemble the md arrays with members on the drives, read-only
+    ///
+    /// Arrays are named `/dev/md/guestctl<pid>_<n>` so they can't clash with
+    /// the host's, and the host mdadm.conf is ignored. Degraded arrays are
+    /// started as long as enough members are present.
+    pub fn md_assemble_all(&mut self) -> Result<Vec<String>> {
+        self.ensure_ready()?;
+
+        let candidates: Vec<PathBuf> = self
+            .drive_devices()
+            .iter()
+            .flat_map(|disk| with_partitions(disk))
+            .collect();
+        if candidates.is_empty() {
+            return Ok(Vec::new());
+        }
+
+        // Exits non-zero when some candidates carry no superblock
+        let output = match Command::new("mdadm")
+            .arg("--examine")
+            .arg("--brief")
+            .args(&candidates)
+            .output()
+        {
+            Ok(output) => output,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => {
+                if self.verbose {
+                    eprintln!("guestfs: mdadm not installed, skipping md assembly");
+                }
+                return Ok(Vec::new());
+            }
+            Err(e) => {
+                return Err(Error::CommandFailed(format!(
+                    "Failed to execute mdadm: {}",
+                    e
+                )))
+            }
+        };
+
+        let mut assembled = Vec::new();
+        for uuid in parse_examine_uuids(&String::from_utf8_lossy(&output.stdout)) {
+            let name = format!(
+                "/dev/md/guestctl{}_{}",
+                std::process::id(),
+                self.md_arrays.len()
+            );
+            if self.verbose {
+                eprintln!("guestfs: assembling md array {} as {}", uuid, name);
+            }
+
+            let output = Command::new("mdadm")
+                .arg("--assemble")
+                .arg(&name)
+                .arg("--readonly")
+                .arg("--run")
+                .arg("--config=none")
+                .arg(format!("--uuid={}", uuid))
+                .args(&candidates)
+                .output()
+                .map_err(|e| Error::CommandFailed(format!("Failed to execute mdadm: {}", e)))?;
+
+            if !output.status.success() {
+                return Err(Error::CommandFailed(format!(
+                    "mdadm assemble of array {} failed: {}",
+                    uuid,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                )));
+            }
+
+            self.md_arrays.push(name.clone());
+            assembled.push(name);
+        }
+
+        Ok(assembled)
+    }
+
+    /// Stop the md arrays assembled at launch
+    pub(crate) fn md_stop_all(&mut self) {
+        for name in self.md_arrays.drain(..).rev() {
+            match Command::new("mdadm").arg("--stop").arg(&name).output() {
+                Ok(output) if output.status.success() => {}
+                Ok(output) => eprintln!(
+                    "Warning: failed to stop md array {}: {}",
+                    name,
+                    String::from_utf8_lossy(&output.stderr).trim()
+                ),
+                Err(e) => eprintln!("Warning: failed to run mdadm for {}: {}", name, e),
+            }
+        }
+    }
+
     /// Create RAID array
     ///
     pub fn md_create(
@@ -90,7 +215,7 @@ impl Guestfs {
 
         let output = Command::new("mdadm")
             .arg("--stop")
-            .arg(format!("/dev/md/{}", md))
+            .arg(md_path(md))
             .output()
             .map_err(|e| Error::CommandFailed(format!("Failed to execute mdadm: {}", e)))?;
 
@@ -115,7 +240,7 @@ impl Guestfs {
 
         let output = Command::new("mdadm")
             .arg("--detail")
-            .arg(format!("/dev/md/{}", md))
+            .arg(md_path(md))
             .output()
             .map_err(|e| Error::CommandFailed(format!("Failed to execute mdadm: {}", e)))?;
 
@@ -140,6 +265,8 @@ impl Guestfs {
 
     /// List MD devices
     ///
+    /// Only the arrays assembled from the drives; `mdadm --detail --scan`
+    /// would also report the host's.
     pub fn list_md_devices(&mut self) -> Result<Vec<String>> {
         self.ensure_ready()?;
 
@@ -147,24 +274,7 @@ impl Guestfs {
             eprintln!("guestfs: list_md_devices");
         }
 
-        let output = Command::new("mdadm")
-            .arg("--detail")
-            .arg("--scan")
-            .output()
-            .map_err(|e| Error::CommandFailed(format!("Failed to execute mdadm: {}", e)))?;
-
-        if !output.status.success() {
-            // No MD devices is not an error
-            return Ok(Vec::new());
-        }
-
-        let output_str = String::from_utf8_lossy(&output.stdout);
-        let devices: Vec<String> = output_str
-            .lines()
-            .filter_map(|line| line.split_whitespace().nth(1).map(|s| s.to_string()))
-            .collect();
-
-        Ok(devices)
+        Ok(self.md_arrays.clone())
     }
 
     /// Get MD array stat
@@ -199,4 +309,27 @@ mod tests {
         let mut g = Guestfs::new().unwrap();
         // API structure tests
     }
+
+    #[test]
+    fn test_parse_examine_uuids() {
+        let output = "\
+ARRAY /dev/md/0  metadata=1.2 UUID=3aaa0122:29827cfa:5331ad66:ca767371 name=web:0
+ARRAY /dev/md/0  metadata=1.2 UUID=3aaa0122:29827cfa:5331ad66:ca767371 name=web:0
+ARRAY /dev/md1 UUID=84bb5e4a:0fbd5e27:c5e3ae11:24b6e09c
+mdadm: No md superblock detected on /dev/loop0.
+";
+        assert_eq!(
+            parse_examine_uuids(output),
+            vec![
+                "3aaa0122:29827cfa:5331ad66:ca767371",
+                "84bb5e4a:0fbd5e27:c5e3ae11:24b6e09c"
+            ]
+        );
+    }
+
+    #[test]
+    fn test_md_path() {
+        assert_eq!(md_path("md0"), "/dev/md/md0");
+        assert_eq!(md_path("/dev/md/guestctl1_0"), "/dev/md/guestctl1_0");
+    }
 }
@@ -5,19 +5,26 @@
 //! allowing disk image inspection and manipulation .
 
 pub mod acl_ops;
+pub mod apfs;
 pub mod archive;
 pub mod attr_ops;
+pub mod auditd_ops;
 pub mod backup_ops;
 pub mod base64_ops;
 pub mod bcache_ops;
+pub mod bitlocker;
 pub mod blockdev_ops;
 pub mod boot;
+pub mod boot_convert;
+pub mod boot_repair;
 pub mod btrfs;
 pub mod cap_ops;
 pub mod checksum;
 pub mod command;
 pub mod compress_ops;
+pub mod containers;
 pub mod cpio_ops;
+pub mod customize;
 pub mod dd_ops;
 pub mod device;
 pub mod device_inventory;
@@ -33,7 +40,9 @@ pub mod fstab_rewriter;
 pub mod glob_ops;
 pub mod grub_ops;
 pub mod handle;
+pub mod hfsplus;
 pub mod hivex_ops;
+pub mod image_properties;
 pub mod inotify_ops;
 pub mod inspect;
 pub mod inspect_enhanced;
@@ -45,6 +54,7 @@ pub mod journal_ops;
 pub mod label_ops;
 pub mod ldm_ops;
 pub mod link_ops;
+pub mod login_ops;
 pub mod luks;
 pub mod lvm;
 pub mod md_ops;
@@ -52,6 +62,7 @@ pub mod metadata;
 pub mod minix_ops;
 pub mod misc;
 pub mod mount;
+pub mod mount_plan;
 pub mod mpath_ops;
 pub mod network;
 pub mod nilfs_ops;
@@ -63,6 +74,7 @@ pub mod part_mgmt;
 pub mod part_type_ops;
 pub mod partition;
 pub mod pread_ops;
+pub mod preview;
 pub mod reiserfs_ops;
 pub mod rsync_ops;
 pub mod security;
@@ -70,12 +82,14 @@ pub mod security_utils;
 pub mod sed_ops;
 pub mod selinux_ops;
 pub mod service;
+pub mod session_trace;
 pub mod smart_ops;
 pub mod squashfs_ops;
 pub mod ssh;
 pub mod swap_ops;
 pub mod sync_ops;
 pub mod syslinux_ops;
+pub mod sysprep_catalog;
 pub mod sysprep_ops;
 pub mod system;
 pub mod system_info;
@@ -98,10 +112,26 @@ pub mod zfs_ops;
 pub mod builder;
 pub mod types;
 
+pub use archive::{ArchiveFormat, ArchiveOptions, ArchiveSummary, TarOptions};
+pub use attr_ops::SecurityAttrs;
+pub use bitlocker::{BitlockerInfo, BitlockerKey};
+pub use boot_repair::{BootRepairOptions, BootRepairReport};
+pub use btrfs::{BtrfsSubvolume, BtrfsUsage};
+pub use containers::{Container, ContainerImage, ContainerInventory, ImageLayer};
+pub use customize::{CustomizeOp, CustomizeReport};
 pub use handle::Guestfs;
+pub use image_properties::{DiskBus, ImageProperties};
 pub use inspect::*;
 pub use inspect_enhanced::*;
+pub use luks::{LuksBinding, LuksInfo, LuksKey};
+pub use lvm::{LvInfo, LvType};
 pub use metadata::Stat;
+pub use mount_plan::{MountFailure, MountPlan, MountSource, PlannedMount, SkippedMount};
+pub use owner_ops::SpecialPermEntry;
+pub use preview::FilePreview;
+pub use rsync_ops::{SyncOptions, SyncStats};
+pub use sysprep_catalog::{SysprepOperation, SysprepResult};
+pub use windows::WindowsScheduledTask;
 
 // Re-export type-safe types for convenience
 pub use builder::GuestfsBuilder;
@@ -18,7 +18,9 @@ impl Guestfs {
     ///
     /// # Arguments
     ///
-    /// * `mountable` - Device name (e.g., "/dev/sda1")
+    /// * `mountable` - Device name (e.g., "/dev/sda1"), btrfs subvolume
+    ///   (e.g., "btrfsvol:/dev/sda2/@home") or live root image (e.g.,
+    ///   "liveimg:/dev/sda1/LiveOS/squashfs.img")
     /// * `mountpoint` - Mount point path (e.g., "/")
     ///
     /// # Examples
@@ -32,27 +34,95 @@ impl Guestfs {
     /// g.mount_ro("/dev/sda1", "/")?;
     /// # Ok::<(), Box<dyn std::error::Error>>(())
     /// ```
-    pub fn mount_ro(&mut self, mountable: &str, mountpoint: &str) -> Result<()> {
-        self.ensure_ready()?;
+    pub(crate) fn mount_ro_untraced(&mut self, mountable: &str, mountpoint: &str) -> Result<()> {
+        self.mount_ro_options("", mountable, mountpoint)
+    }
 
+    /// Mount a filesystem read-only with further mount options
+    ///
+    /// `options` (comma separated) follow the ones read-only mounting needs.
+    pub fn mount_ro_options(
+        &mut self,
+        options: &str,
+        mountable: &str,
+        mountpoint: &str,
+    ) -> Result<()> {
         if self.verbose {
-            eprintln!("guestfs: mount_ro {} {}", mountable, mountpoint);
+            eprintln!(
+                "guestfs: mount_ro_options {} {} {}",
+                options, mountable, mountpoint
+            );
+        }
+
+        self.mount_device(options, mountable, mountpoint, true)
+    }
+
+    /// Mount a filesystem read-write with further mount options
+    ///
+    /// Unlike [`Guestfs::mount`], this mounts the filesystem on the host, so
+    /// that file writes and commands run in the guest change it.
+    pub fn mount_rw_options(
+        &mut self,
+        options: &str,
+        mountable: &str,
+        mountpoint: &str,
+    ) -> Result<()> {
+        if self.verbose {
+            eprintln!(
+                "guestfs: mount_rw_options {} {} {}",
+                options, mountable, mountpoint
+            );
+        }
+
+        if self.drives.first().is_some_and(|drive| drive.readonly) {
+            return Err(Error::PermissionDenied(
+                "Cannot mount read-write on read-only drive".to_string(),
+            ));
         }
 
+        self.mount_device(options, mountable, mountpoint, false)
+    }
+
+    /// Mount a filesystem on the host below the mount root
+    fn mount_device(
+        &mut self,
+        options: &str,
+        mountable: &str,
+        mountpoint: &str,
+        read_only: bool,
+    ) -> Result<()> {
+        self.ensure_ready()?;
+
         // Check if this device is already mounted - prevent duplicate mounts
         if self.mounted.contains_key(mountable) {
             return Ok(());
         }
 
+        // btrfsvol:DEVICE/SUBVOLUME mounts a subvolume of DEVICE,
+        // liveimg:DEVICE/IMAGE a root image on the filesystem of DEVICE
+        let (device, subvolume, image) = if let Some((device, image)) = super::iso::parse_liveimg(mountable) {
+            (device, None, Some(image))
+        } else {
+            match super::btrfs::parse_btrfsvol(mountable) {
+                Some((device, subvolume)) => (device, Some(subvolume), None),
+                None => (mountable, None, None),
+            }
+        };
+
+        if image.is_some() && !read_only {
+            return Err(Error::Unsupported(
+                "live root images can only be mounted read-only".to_string(),
+            ));
+        }
+
         // Determine the actual device path to mount
-        let device_partition = if mountable.starts_with("/dev/mapper/")
-            || (mountable.starts_with("/dev/") && mountable.matches('/').count() >= 3) {
-            // LVM logical volume (/dev/mapper/* or /dev/vgname/lvname) - use the path directly
-            // These device nodes are created by LVM on top of the underlying block device
-            std::path::PathBuf::from(mountable)
+        let device_partition = if let Some(path) = self.direct_device_path(device) {
+            // LVM logical volume (/dev/mapper/* or /dev/vgname/lvname), md array or
+            // partition of a further drive - use the host device node directly
+            path
         } else {
             // Parse device name to get partition number
-            let partition_num = self.parse_device_name(mountable)?;
+            let partition_num = self.parse_device_name(device)?;
 
             // Get the actual device path (loop or NBD)
             if let Some(loop_dev) = &self.loop_device {
@@ -108,10 +178,16 @@ impl Guestfs {
         let need_sudo = unsafe { libc::geteuid() } != 0;
 
         // Detect filesystem type to use appropriate mount options
-        // Use the original mountable parameter, as device_partition might not exist yet (LVM)
-        let fs_type = self.vfs_type(mountable)
+        // Use the guest device name, as device_partition might not exist yet (LVM)
+        let fs_type = self.vfs_type(device)
             .unwrap_or_else(|_| "auto".to_string());
 
+        // Live root images are files on carrier filesystems mounted first
+        let (device_partition, carriers) = match image {
+            Some(image) => self.mount_live_image(device, &device_partition, image)?,
+            None => (device_partition, Vec::new()),
+        };
+
         // Build mount command
         let mut cmd = if need_sudo {
             let mut sudo_cmd = Command::new("sudo");
@@ -121,27 +197,51 @@ impl Guestfs {
             Command::new("mount")
         };
 
+        if image.is_some() {
+            if let Ok(Some(vfs_type)) = super::squashfs_ops::image_vfs_type(&device_partition) {
+                cmd.arg("-t").arg(vfs_type);
+            }
+        }
+
         // Use filesystem-specific mount options
         // For ext* filesystems: use noload to prevent journal updates on read-only mounts
         // For XFS: use norecovery to skip log replay (which requires write access)
+        // For btrfs subvolumes: select the subvolume (relative to the top level)
+        // For live root images: loop mount the image file
         // For btrfs and others: just use ro
-        let mount_opts = if fs_type.starts_with("ext") {
-            "ro,noload"
+        let mount_opts = if !read_only {
+            match subvolume {
+                Some(subvolume) => format!("rw,subvol=/{}", subvolume),
+                None => "rw".to_string(),
+            }
+        } else if let Some(subvolume) = subvolume {
+            format!("ro,subvol=/{}", subvolume)
+        } else if image.is_some() {
+            "ro,loop".to_string()
+        } else if fs_type.starts_with("ext") {
+            "ro,noload".to_string()
         } else if fs_type == "xfs" {
-            "ro,norecovery"
+            "ro,norecovery".to_string()
+        } else {
+            "ro".to_string()
+        };
+        let mount_opts = if options.is_empty() {
+            mount_opts
         } else {
-            "ro"
+            format!("{},{}", mount_opts, options)
         };
 
         let output = cmd
             .arg("-o")
-            .arg(mount_opts)
+            .arg(&mount_opts)
             .arg(&device_partition)
             .arg(&actual_mountpoint)
             .output()
-            .map_err(|e| Error::CommandFailed(format!("Failed to execute mount: {}", e)))?;
+            .map_err(|e| Error::CommandFailed(format!("Failed to execute mount: {}", e)))
+            .inspect_err(|_| super::iso::release_carriers(&carriers))?;
 
         if !output.status.success() {
+            super::iso::release_carriers(&carriers);
             let stderr = String::from_utf8_lossy(&output.stderr);
             return Err(Error::CommandFailed(format!(
                 "Mount failed: {}. You may need sudo/root permissions.",
@@ -154,13 +254,16 @@ impl Guestfs {
             mountable.to_string(),
             actual_mountpoint.to_string_lossy().to_string(),
         );
+        if !carriers.is_empty() {
+            self.live_carriers.insert(mountable.to_string(), carriers);
+        }
 
         Ok(())
     }
 
     /// Mount a filesystem read-write
     ///
-    pub fn mount(&mut self, mountable: &str, mountpoint: &str) -> Result<()> {
+    pub(crate) fn mount_untraced(&mut self, mountable: &str, mountpoint: &str) -> Result<()> {
         self.ensure_ready()?;
 
         // Check if readonly
@@ -225,7 +328,7 @@ impl Guestfs {
 
     /// Unmount a filesystem
     ///
-    pub fn umount(&mut self, pathordevice: &str) -> Result<()> {
+    pub(crate) fn umount_untraced(&mut self, pathordevice: &str) -> Result<()> {
         self.ensure_ready()?;
 
         if self.trace {
@@ -281,6 +384,7 @@ impl Guestfs {
 
             // Remove from tracking
             self.mounted.remove(&dev);
+            self.release_live_carriers(&dev);
         }
 
         Ok(())
@@ -421,6 +525,12 @@ impl Guestfs {
 
         self.mounted.clear();
 
+        // Then the filesystems live root images were mounted from
+        let live_mountables: Vec<String> = self.live_carriers.keys().cloned().collect();
+        for mountable in live_mountables {
+            self.release_live_carriers(&mountable);
+        }
+
         // Sync filesystem to ensure all unmounts are complete
         if let Err(e) = std::process::Command::new("sync").output() {
             eprintln!("Warning: sync command failed: {}", e);
@@ -0,0 +1,653 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Mount planning from the guest's fstab
+//!
+//! Mounting every filesystem `inspect_get_mountpoints` lists ignores what the
+//! fstab says about them. A plan follows the fstab the way the guest's boot
+//! would, read-only:
+//!
+//! - `noauto` entries are skipped, unless `x-systemd.automount` mounts them
+//!   on first access
+//! - `nofail` entries may fail without failing the plan
+//! - `bind` and `rbind` entries bind-mount a directory of the guest
+//! - `x-systemd.requires-mounts-for=PATH` orders the entry after the mounts
+//!   PATH needs
+//! - swap, pseudo and network filesystems are not mounted
+//!
+//! Mounts come after the mounts their mountpoint lies in, by depth and then in
+//! fstab order. Of the fstab options only `ro`, `noexec`, `nosuid` and
+//! `nodev` are passed to mount; filesystem specific ones may not suit the
+//! host kernel or a read-only mount. Options set with
+//! [`Guestfs::set_fstab_options`] replace those of every entry and are passed
+//! as given.
+
+use crate::core::{Error, Result};
+use crate::guestfs::fstab::FstabEntry;
+use crate::guestfs::Guestfs;
+use serde::Serialize;
+use std::fs;
+use std::process::Command;
+use std::sync::RwLock;
+
+/// fstab options passed on to mount
+const PASSED_OPTIONS: [&str; 4] = ["ro", "noexec", "nosuid", "nodev"];
+
+/// Options that only steer planning, never passed to mount
+const PLANNING_OPTIONS: [&str; 11] = [
+    "defaults", "auto", "noauto", "nofail", "bind", "rbind", "rw", "user", "users", "owner",
+    "_netdev",
+];
+
+/// Filesystems the kernel provides at runtime, nothing to inspect
+const PSEUDO_FILESYSTEMS: [&str; 21] = [
+    "proc",
+    "sysfs",
+    "devpts",
+    "devtmpfs",
+    "tmpfs",
+    "ramfs",
+    "cgroup",
+    "cgroup2",
+    "securityfs",
+    "debugfs",
+    "tracefs",
+    "efivarfs",
+    "hugetlbfs",
+    "mqueue",
+    "pstore",
+    "bpf",
+    "configfs",
+    "fusectl",
+    "binfmt_misc",
+    "autofs",
+    "overlay",
+];
+
+/// Filesystems served by other hosts
+const NETWORK_FILESYSTEMS: [&str; 10] = [
+    "nfs",
+    "nfs4",
+    "cifs",
+    "smb3",
+    "smbfs",
+    "glusterfs",
+    "ceph",
+    "sshfs",
+    "fuse.sshfs",
+    "9p",
+];
+
+/// Options new handles use instead of the fstab's (see [`set_default_fstab_options`])
+static DEFAULT_FSTAB_OPTIONS: RwLock<Option<String>> = RwLock::new(None);
+
+/// Set the options that handles created from now on use instead of the fstab's
+///
+/// Meant for front ends that take the choice once (e.g. on the command line).
+/// See [`Guestfs::set_fstab_options`].
+pub fn set_default_fstab_options(options: Option<String>) {
+    let mut default = DEFAULT_FSTAB_OPTIONS
+        .write()
+        .unwrap_or_else(|e| e.into_inner());
+    *default = options;
+}
+
+/// fstab options override that new handles start with
+pub(crate) fn default_fstab_options() -> Option<String> {
+    DEFAULT_FSTAB_OPTIONS
+        .read()
+        .unwrap_or_else(|e| e.into_inner())
+        .clone()
+}
+
+/// What a planned mount puts at its mountpoint
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+#[serde(tag = "type", content = "path", rename_all = "lowercase")]
+pub enum MountSource {
+    /// Filesystem on a device (a mountable as `inspect_os` returns them)
+    Device(String),
+    /// Directory of the guest, bind-mounted
+    Bind(String),
+}
+
+/// Filesystem a plan mounts
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PlannedMount {
+    pub mountpoint: String,
+    pub source: MountSource,
+    /// Filesystem type from the fstab (empty if unknown)
+    pub fstype: String,
+    /// Options passed to mount, besides those read-only mounting adds
+    pub options: Vec<String>,
+    /// The guest boots without it
+    pub nofail: bool,
+    /// Paths whose mounts must come first (`x-systemd.requires-mounts-for`)
+    pub requires: Vec<String>,
+}
+
+/// fstab entry a plan does not mount
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SkippedMount {
+    pub mountpoint: String,
+    pub spec: String,
+    pub reason: String,
+    pub nofail: bool,
+}
+
+/// Mounts of an OS in the order to make them
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct MountPlan {
+    pub mounts: Vec<PlannedMount>,
+    pub skipped: Vec<SkippedMount>,
+}
+
+/// Planned mount that failed
+#[derive(Debug, Clone, Serialize)]
+pub struct MountFailure {
+    pub mountpoint: String,
+    pub error: String,
+    pub nofail: bool,
+}
+
+/// Mountpoint without trailing slashes
+fn normalize_mountpoint(mountpoint: &str) -> String {
+    let trimmed = mountpoint.trim_end_matches('/');
+    if trimmed.is_empty() {
+        "/".to_string()
+    } else {
+        trimmed.to_string()
+    }
+}
+
+/// Number of components of a path
+fn depth(path: &str) -> usize {
+    path.split('/').filter(|part| !part.is_empty()).count()
+}
+
+/// Whether `path` is `dir` or lies below it
+fn is_under(path: &str, dir: &str) -> bool {
+    dir == "/"
+        || path == dir
+        || path
+            .strip_prefix(dir)
+            .is_some_and(|rest| rest.starts_with('/'))
+}
+
+/// Options of an entry to pass to mount
+fn mount_options(options: &str, overridden: bool) -> Vec<String> {
+    options
+        .split(',')
+        .filter(|option| !option.is_empty())
+        .filter(|option| {
+            if overridden {
+                !PLANNING_OPTIONS.contains(option)
+                    && !option.starts_with("x-")
+                    && !option.starts_with("comment=")
+            } else {
+                PASSED_OPTIONS.contains(option)
+            }
+        })
+        .map(str::to_string)
+        .collect()
+}
+
+/// Plan the mounts of the OS at `root` from its fstab entries
+///
+/// `override_options` replaces the options of every entry. `resolve` finds
+/// the mountable an entry's device spec refers to.
+pub(crate) fn plan_entries(
+    root: &str,
+    entries: &[FstabEntry],
+    override_options: Option<&str>,
+    mut resolve: impl FnMut(&FstabEntry) -> Option<String>,
+) -> MountPlan {
+    let options_of =
+        |entry: &FstabEntry| -> String { override_options.unwrap_or(&entry.options).to_string() };
+
+    let root_options = entries
+        .iter()
+        .find(|entry| normalize_mountpoint(&entry.mountpoint) == "/")
+        .map(|entry| (options_of(entry), entry.fstype.clone()));
+    let (root_options, root_fstype) = match root_options {
+        Some((options, fstype)) => (options, fstype),
+        None => (
+            override_options.unwrap_or_default().to_string(),
+            String::new(),
+        ),
+    };
+
+    let mut plan = MountPlan::default();
+    plan.mounts.push(PlannedMount {
+        mountpoint: "/".to_string(),
+        source: MountSource::Device(root.to_string()),
+        fstype: root_fstype,
+        options: mount_options(&root_options, override_options.is_some()),
+        nofail: false,
+        requires: Vec::new(),
+    });
+
+    for entry in entries {
+        if !entry.mountpoint.starts_with('/')
+            || entry.fstype == "swap"
+            || PSEUDO_FILESYSTEMS.contains(&entry.fstype.as_str())
+        {
+            continue;
+        }
+        let mountpoint = normalize_mountpoint(&entry.mountpoint);
+        if mountpoint == "/" {
+            continue;
+        }
+
+        let options = options_of(entry);
+        let flags: Vec<&str> = options.split(',').collect();
+        let nofail = flags.contains(&"nofail");
+        let skip = |reason: &str| SkippedMount {
+            mountpoint: mountpoint.clone(),
+            spec: entry.spec.clone(),
+            reason: reason.to_string(),
+            nofail,
+        };
+
+        if flags.contains(&"noauto") && !flags.contains(&"x-systemd.automount") {
+            plan.skipped.push(skip("noauto"));
+            continue;
+        }
+        if NETWORK_FILESYSTEMS.contains(&entry.fstype.as_str()) || flags.contains(&"_netdev") {
+            plan.skipped.push(skip("network filesystem"));
+            continue;
+        }
+
+        let source = if flags.contains(&"bind") || flags.contains(&"rbind") {
+            if !entry.spec.starts_with('/') {
+                plan.skipped.push(skip("bind source is not a path"));
+                continue;
+            }
+            MountSource::Bind(normalize_mountpoint(&entry.spec))
+        } else {
+            match resolve(entry) {
+                Some(mountable) => MountSource::Device(mountable),
+                None => {
+                    plan.skipped.push(skip("device not found"));
+                    continue;
+                }
+            }
+        };
+
+        let requires = flags
+            .iter()
+            .filter_map(|flag| flag.strip_prefix("x-systemd.requires-mounts-for="))
+            .map(normalize_mountpoint)
+            .collect();
+
+        plan.mounts.push(PlannedMount {
+            mountpoint,
+            source,
+            fstype: entry.fstype.clone(),
+            options: mount_options(&options, override_options.is_some()),
+            nofail,
+            requires,
+        });
+    }
+
+    plan.mounts = order_mounts(plan.mounts);
+    plan
+}
+
+/// Order mounts after the mounts they depend on
+///
+/// A mount depends on the mounts its mountpoint, bind source and required
+/// paths lie in. Among mounts whose dependencies are all mounted the
+/// shallowest comes first, then the earliest in the fstab. Mounts in a
+/// dependency cycle keep that order.
+pub(crate) fn order_mounts(mounts: Vec<PlannedMount>) -> Vec<PlannedMount> {
+    let count = mounts.len();
+    let dependencies: Vec<Vec<usize>> = mounts
+        .iter()
+        .enumerate()
+        .map(|(index, mount)| {
+            let mut paths = mount.requires.clone();
+            if let MountSource::Bind(source) = &mount.source {
+                paths.push(source.clone());
+            }
+            (0..count)
+                .filter(|&other| other != index)
+                .filter(|&other| {
+                    let dir = &mounts[other].mountpoint;
+                    (dir != &mount.mountpoint && is_under(&mount.mountpoint, dir))
+                        || paths.iter().any(|path| is_under(path, dir))
+                })
+                .collect()
+        })
+        .collect();
+
+    let key = |index: usize| (depth(&mounts[index].mountpoint), index);
+    let mut done = vec![false; count];
+    let mut order = Vec::with_capacity(count);
+    while order.len() < count {
+        let ready = (0..count)
+            .filter(|&index| !done[index])
+            .filter(|&index| dependencies[index].iter().all(|&other| done[other]))
+            .min_by_key(|&index| key(index));
+        // Break a cycle with the mount that would come first without it
+        let next = ready.or_else(|| {
+            (0..count)
+                .filter(|&index| !done[index])
+                .min_by_key(|&index| key(index))
+        });
+        let Some(next) = next else { break };
+        done[next] = true;
+        order.push(next);
+    }
+
+    let mut mounts: Vec<Option<PlannedMount>> = mounts.into_iter().map(Some).collect();
+    order
+        .into_iter()
+        .filter_map(|index| mounts[index].take())
+        .collect()
+}
+
+impl Guestfs {
+    /// Use `options` instead of each fstab entry's options
+    ///
+    /// The override decides `noauto`, `nofail` and `bind` too: with
+    /// `defaults`, `noauto` entries are mounted as well. Options other than
+    /// those are passed to mount as given (e.g. `nouuid` for cloned XFS).
+    pub fn set_fstab_options(&mut self, options: Option<&str>) {
+        self.fstab_options = options.map(str::to_string);
+    }
+
+    /// Get the fstab options override
+    pub fn get_fstab_options(&self) -> Option<&str> {
+        self.fstab_options.as_deref()
+    }
+
+    /// Plan the mounts of the OS at `root` from its fstab
+    ///
+    /// Without a readable fstab the plan only mounts `root`.
+    pub fn inspect_mount_plan(&mut self, root: &str) -> Result<MountPlan> {
+        self.ensure_ready()?;
+
+        let (entries, devices) = self.fstab_entries(root).unwrap_or_default();
+        let override_options = self.fstab_options.clone();
+        Ok(plan_entries(
+            root,
+            &entries,
+            override_options.as_deref(),
+            |entry| {
+                self.resolve_fstab_spec(&entry.spec, &devices)
+                    .map(|device| entry.mountable(device))
+            },
+        ))
+    }
+
+    /// Mount a plan read-only, in its order
+    ///
+    /// Fails only if the root cannot be mounted; the mounts that failed
+    /// after it are returned.
+    pub fn mount_plan_ro(&mut self, plan: &MountPlan) -> Result<Vec<MountFailure>> {
+        self.mount_plan(plan, true)
+    }
+
+    /// Mount a plan read-write, in its order (see [`Guestfs::mount_plan_ro`])
+    pub fn mount_plan_rw(&mut self, plan: &MountPlan) -> Result<Vec<MountFailure>> {
+        self.mount_plan(plan, false)
+    }
+
+    fn mount_plan(&mut self, plan: &MountPlan, read_only: bool) -> Result<Vec<MountFailure>> {
+        let mut failures = Vec::new();
+        for mount in &plan.mounts {
+            let options = mount.options.join(",");
+            let result = match &mount.source {
+                MountSource::Device(mountable) if read_only => {
+                    self.mount_ro_options(&options, mountable, &mount.mountpoint)
+                }
+                MountSource::Device(mountable) => {
+                    self.mount_rw_options(&options, mountable, &mount.mountpoint)
+                }
+                MountSource::Bind(source) => self.mount_bind(source, &mount.mountpoint, read_only),
+            };
+            match result {
+                Ok(()) => {}
+                Err(e) if mount.mountpoint == "/" => return Err(e),
+                Err(e) => failures.push(MountFailure {
+                    mountpoint: mount.mountpoint.clone(),
+                    error: e.to_string(),
+                    nofail: mount.nofail,
+                }),
+            }
+        }
+        Ok(failures)
+    }
+
+    /// Mount the filesystems of the OS at `root` read-only as its fstab says
+    ///
+    /// Mounts the guest needs to boot that are missing or fail are reported
+    /// on stderr, as the inspection then sees the directory below them.
+    pub fn mount_os_ro(&mut self, root: &str) -> Result<MountPlan> {
+        self.mount_os(root, true)
+    }
+
+    /// Mount the filesystems of the OS at `root` read-write as its fstab says
+    ///
+    /// For changes to the guest, such as boot repair (see
+    /// [`Guestfs::mount_os_ro`]).
+    pub fn mount_os_rw(&mut self, root: &str) -> Result<MountPlan> {
+        self.mount_os(root, false)
+    }
+
+    fn mount_os(&mut self, root: &str, read_only: bool) -> Result<MountPlan> {
+        let plan = self.inspect_mount_plan(root)?;
+        let failures = self.mount_plan(&plan, read_only)?;
+
+        for skipped in &plan.skipped {
+            if skipped.reason == "device not found" && !skipped.nofail {
+                eprintln!(
+                    "Warning: {} not mounted: device {} not found",
+                    skipped.mountpoint, skipped.spec
+                );
+            }
+        }
+        for failure in failures.iter().filter(|failure| !failure.nofail) {
+            eprintln!(
+                "Warning: {} not mounted: {}",
+                failure.mountpoint, failure.error
+            );
+        }
+        Ok(plan)
+    }
+
+    /// Bind-mount the guest directory `source` read-only at `mountpoint`
+    pub fn mount_bind_ro(&mut self, source: &str, mountpoint: &str) -> Result<()> {
+        self.mount_bind(source, mountpoint, true)
+    }
+
+    fn mount_bind(&mut self, source: &str, mountpoint: &str, read_only: bool) -> Result<()> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!(
+                "guestfs: mount_bind {} {} (ro: {})",
+                source, mountpoint, read_only
+            );
+        }
+
+        if self.mounted.contains_key(mountpoint) {
+            return Ok(());
+        }
+
+        let mount_root = self
+            .mount_root
+            .clone()
+            .ok_or_else(|| Error::InvalidState("Nothing mounted to bind from".to_string()))?;
+        let actual_source = mount_root.join(source.trim_start_matches('/'));
+        let actual_mountpoint = mount_root.join(mountpoint.trim_start_matches('/'));
+        if !actual_source.is_dir() {
+            return Err(Error::NotFound(format!("Bind source {} not found", source)));
+        }
+        fs::create_dir_all(&actual_mountpoint)
+            .map_err(|e| Error::CommandFailed(format!("Failed to create mountpoint: {}", e)))?;
+
+        let mut cmd = if unsafe { libc::geteuid() } != 0 {
+            let mut sudo_cmd = Command::new("sudo");
+            sudo_cmd.arg("mount");
+            sudo_cmd
+        } else {
+            Command::new("mount")
+        };
+        // mount remounts the bind mount read-only itself
+        let output = cmd
+            .arg("-o")
+            .arg(if read_only { "bind,ro" } else { "bind" })
+            .arg(&actual_source)
+            .arg(&actual_mountpoint)
+            .output()
+            .map_err(|e| Error::CommandFailed(format!("Failed to execute mount: {}", e)))?;
+        if !output.status.success() {
+            return Err(Error::CommandFailed(format!(
+                "Bind mount failed: {}",
+                String::from_utf8_lossy(&output.stderr).trim()
+            )));
+        }
+
+        self.mounted.insert(
+            mountpoint.to_string(),
+            actual_mountpoint.to_string_lossy().to_string(),
+        );
+
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn entry(spec: &str, mountpoint: &str, fstype: &str, options: &str) -> FstabEntry {
+        FstabEntry::parse(&format!(
+            "{} {} {} {} 0 0",
+            spec, mountpoint, fstype, options
+        ))
+        .unwrap()
+    }
+
+    fn resolve(entry: &FstabEntry) -> Option<String> {
+        entry
+            .spec
+            .strip_prefix("UUID=")
+            .filter(|uuid| *uuid != "gone")
+            .map(|uuid| format!("/dev/{}", uuid))
+    }
+
+    fn mountpoints(plan: &MountPlan) -> Vec<&str> {
+        plan.mounts
+            .iter()
+            .map(|mount| mount.mountpoint.as_str())
+            .collect()
+    }
+
+    #[test]
+    fn test_plan_split_layout() {
+        let entries = [
+            entry("UUID=sda3", "/var/log", "xfs", "defaults,noexec"),
+            entry("UUID=sda1", "/", "ext4", "defaults"),
+            entry(
+                "UUID=sda2",
+                "/var",
+                "xfs",
+                "nodev,nosuid,x-systemd.device-timeout=0",
+            ),
+            entry("UUID=sda4", "/usr/", "ext4", "ro"),
+            entry("UUID=sda5", "none", "swap", "sw"),
+            entry("proc", "/proc", "proc", "defaults"),
+            entry("tmpfs", "/tmp", "tmpfs", "defaults"),
+        ];
+        let plan = plan_entries("/dev/sda1", &entries, None, resolve);
+
+        assert_eq!(mountpoints(&plan), ["/", "/var", "/usr", "/var/log"]);
+        assert_eq!(plan.mounts[1].options, ["nodev", "nosuid"]);
+        assert_eq!(plan.mounts[2].options, ["ro"]);
+        assert_eq!(plan.mounts[3].options, ["noexec"]);
+        assert_eq!(
+            plan.mounts[3].source,
+            MountSource::Device("/dev/sda3".to_string())
+        );
+        assert!(plan.skipped.is_empty());
+    }
+
+    #[test]
+    fn test_plan_options() {
+        let entries = [
+            entry("UUID=sda2", "/boot", "ext4", "noauto"),
+            entry("UUID=sda3", "/data", "ext4", "noauto,x-systemd.automount"),
+            entry("UUID=gone", "/scratch", "ext4", "nofail"),
+            entry("UUID=gone", "/srv", "ext4", "defaults"),
+            entry("server:/export", "/mnt/nfs", "nfs", "defaults"),
+            entry("/data/www", "/var/www", "none", "bind"),
+        ];
+        let plan = plan_entries("/dev/sda1", &entries, None, resolve);
+
+        assert_eq!(mountpoints(&plan), ["/", "/data", "/var/www"]);
+        assert_eq!(
+            plan.mounts[2].source,
+            MountSource::Bind("/data/www".to_string())
+        );
+        let skipped: Vec<(&str, &str, bool)> = plan
+            .skipped
+            .iter()
+            .map(|s| (s.mountpoint.as_str(), s.reason.as_str(), s.nofail))
+            .collect();
+        assert_eq!(
+            skipped,
+            [
+                ("/boot", "noauto", false),
+                ("/scratch", "device not found", true),
+                ("/srv", "device not found", false),
+                ("/mnt/nfs", "network filesystem", false),
+            ]
+        );
+
+        // The override decides noauto too, and passes options as given
+        let plan = plan_entries("/dev/sda1", &entries[..1], Some("defaults,nouuid"), resolve);
+        assert_eq!(mountpoints(&plan), ["/", "/boot"]);
+        assert_eq!(plan.mounts[0].options, ["nouuid"]);
+        assert_eq!(plan.mounts[1].options, ["nouuid"]);
+    }
+
+    #[test]
+    fn test_order_mounts() {
+        let entries = [
+            entry("/srv/data", "/home", "none", "bind"),
+            entry("UUID=sda2", "/srv", "ext4", "defaults"),
+            entry(
+                "UUID=sda3",
+                "/opt",
+                "ext4",
+                "x-systemd.requires-mounts-for=/var/lib",
+            ),
+            entry("UUID=sda4", "/var/lib", "ext4", "defaults"),
+            entry("UUID=sda5", "/var", "ext4", "defaults"),
+        ];
+        let plan = plan_entries("/dev/sda1", &entries, None, resolve);
+        assert_eq!(
+            mountpoints(&plan),
+            ["/", "/srv", "/home", "/var", "/var/lib", "/opt"]
+        );
+        assert_eq!(plan.mounts[5].requires, ["/var/lib"]);
+
+        // Mounts in a cycle keep depth and fstab order
+        let entries = [
+            entry("/b", "/a", "none", "bind"),
+            entry("/a", "/b", "none", "bind"),
+        ];
+        let plan = plan_entries("/dev/sda1", &entries, None, resolve);
+        assert_eq!(mountpoints(&plan), ["/", "/a", "/b"]);
+    }
+
+    #[test]
+    fn test_is_under() {
+        assert!(is_under("/var/log", "/var"));
+        assert!(is_under("/var", "/var"));
+        assert!(is_under("/var", "/"));
+        assert!(!is_under("/variable", "/var"));
+        assert_eq!(normalize_mountpoint("/usr/"), "/usr");
+        assert_eq!(normalize_mountpoint("//"), "/");
+    }
+}
@@ -6,7 +6,120 @@
 use crate::core::{Error, Result};
 use crate::guestfs::Guestfs;
 
+/// A file carrying setuid, setgid or world-writable permission bits
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SpecialPermEntry {
+    /// Guest path of the file
+    pub path: String,
+    /// Full permission bits (including setuid/setgid/sticky)
+    pub mode: u32,
+    pub uid: u32,
+    pub gid: u32,
+    /// find(1) type letter: 'f' regular file, 'd' directory, ...
+    pub file_type: char,
+}
+
+impl SpecialPermEntry {
+    pub fn is_setuid(&self) -> bool {
+        self.mode & 0o4000 != 0
+    }
+
+    pub fn is_setgid(&self) -> bool {
+        self.mode & 0o2000 != 0
+    }
+
+    pub fn is_sticky(&self) -> bool {
+        self.mode & 0o1000 != 0
+    }
+
+    pub fn is_world_writable(&self) -> bool {
+        self.mode & 0o0002 != 0
+    }
+
+    pub fn is_dir(&self) -> bool {
+        self.file_type == 'd'
+    }
+}
+
+/// Parse one `find -printf '%m %U %G %y %p\n'` line, stripping the host prefix
+fn parse_special_perm_line(line: &str, prefix: &str) -> Option<SpecialPermEntry> {
+    let mut parts = line.splitn(5, ' ');
+    let mode = u32::from_str_radix(parts.next()?, 8).ok()?;
+    let uid = parts.next()?.parse().ok()?;
+    let gid = parts.next()?.parse().ok()?;
+    let file_type = parts.next()?.chars().next()?;
+    let host_path = parts.next()?;
+
+    let guest_path = host_path.strip_prefix(prefix).unwrap_or(host_path);
+    let path = if guest_path.is_empty() {
+        "/".to_string()
+    } else if guest_path.starts_with('/') {
+        guest_path.to_string()
+    } else {
+        format!("/{}", guest_path)
+    };
+
+    Some(SpecialPermEntry {
+        path,
+        mode,
+        uid,
+        gid,
+        file_type,
+    })
+}
+
 impl Guestfs {
+    /// Find setuid, setgid and world-writable files below a directory
+    ///
+    /// Symlinks are skipped since their permission bits are meaningless.
+    pub fn find_special_perms(&mut self, directory: &str) -> Result<Vec<SpecialPermEntry>> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: find_special_perms {}", directory);
+        }
+
+        let host_path = self.resolve_guest_path(directory)?;
+
+        let output = std::process::Command::new("find")
+            .arg(&host_path)
+            .args(["!", "-type", "l"])
+            .args(["(", "-perm", "-4000", "-o", "-perm", "-2000", "-o", "-perm", "-0002", ")"])
+            .args(["-printf", "%m %U %G %y %p\\n"])
+            .output()
+            .map_err(|e| Error::CommandFailed(format!("Failed to execute find: {}", e)))?;
+
+        // find exits non-zero on unreadable directories but still reports
+        // everything it could reach, so only fail when nothing was produced
+        if !output.status.success() && output.stdout.is_empty() {
+            return Err(Error::CommandFailed(format!(
+                "Find failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        let prefix = host_path.to_string_lossy();
+        let prefix = prefix.trim_end_matches('/');
+        let base = directory.trim_end_matches('/');
+
+        Ok(stdout
+            .lines()
+            .filter_map(|line| parse_special_perm_line(line, prefix))
+            .map(|mut entry| {
+                // Paths are relative to `directory`; re-anchor them in the guest
+                if !base.is_empty() {
+                    entry.path = if entry.path == "/" {
+                        base.to_string()
+                    } else {
+                        format!("{}{}", base, entry.path)
+                    };
+                }
+                entry
+            })
+            .collect())
+    }
+
     /// Change ownership recursively
     ///
     pub fn chown_recursive(&mut self, owner: i32, group: i32, path: &str) -> Result<()> {
@@ -221,4 +334,29 @@ mod tests {
         let mut g = Guestfs::new().unwrap();
         // API structure tests
     }
+
+    #[test]
+    fn test_parse_special_perm_line() {
+        let entry =
+            parse_special_perm_line("4755 0 0 f /tmp/mnt/usr/bin/passwd", "/tmp/mnt").unwrap();
+        assert_eq!(entry.path, "/usr/bin/passwd");
+        assert_eq!(entry.mode, 0o4755);
+        assert!(entry.is_setuid());
+        assert!(!entry.is_setgid());
+        assert!(!entry.is_world_writable());
+
+        let entry = parse_special_perm_line("1777 0 0 d /tmp/mnt/tmp", "/tmp/mnt").unwrap();
+        assert!(entry.is_dir());
+        assert!(entry.is_sticky());
+        assert!(entry.is_world_writable());
+
+        let entry =
+            parse_special_perm_line("2755 0 42 f /tmp/mnt/usr/bin/with space", "/tmp/mnt")
+                .unwrap();
+        assert_eq!(entry.path, "/usr/bin/with space");
+        assert_eq!(entry.gid, 42);
+        assert!(entry.is_setgid());
+
+        assert!(parse_special_perm_line("garbage", "/tmp/mnt").is_none());
+    }
 }
@@ -4,12 +4,401 @@
 //! This implementation provides package inspection capabilities.
 
 use crate::core::{Error, Result};
-use crate::guestfs::Guestfs;
+use crate::guestfs::{Application, Guestfs};
+use std::collections::HashMap;
+
+const DPKG_STATUS: &str = "/var/lib/dpkg/status";
+const APK_INSTALLED: &str = "/lib/apk/db/installed";
+const PACMAN_LOCAL: &str = "/var/lib/pacman/local";
+const PORTAGE_DB: &str = "/var/db/pkg";
+const RPM_QUERY_FORMAT: &str =
+    "%{NAME}\\t%{EPOCHNUM}\\t%{VERSION}\\t%{RELEASE}\\t%{ARCH}\\t%{SOURCERPM}\\n";
+
+/// Parse pacman's `desc` and `files` database entries into (path, package) pairs
+fn parse_pacman_entry(desc: &str, files: &str) -> Vec<(String, String)> {
+    let mut lines = desc.lines();
+    let mut name = None;
+    while let Some(line) = lines.next() {
+        if line == "%NAME%" {
+            name = lines.next().map(|s| s.trim().to_string());
+            break;
+        }
+    }
+
+    let Some(name) = name else {
+        return Vec::new();
+    };
+
+    files
+        .lines()
+        .skip_while(|line| *line != "%FILES%")
+        .skip(1)
+        .take_while(|line| !line.is_empty() && !line.starts_with('%'))
+        .map(|line| (format!("/{}", line.trim_end_matches('/')), name.clone()))
+        .collect()
+}
+
+/// Parse apk's `installed` database into (path, package) pairs
+fn parse_apk_installed(content: &str) -> Vec<(String, String)> {
+    let mut owners = Vec::new();
+    let mut package = String::new();
+    let mut directory = String::new();
+
+    for line in content.lines() {
+        if let Some(name) = line.strip_prefix("P:") {
+            package = name.to_string();
+            directory.clear();
+        } else if let Some(dir) = line.strip_prefix("F:") {
+            directory = dir.to_string();
+            owners.push((format!("/{}", directory), package.clone()));
+        } else if let Some(file) = line.strip_prefix("R:") {
+            let path = if directory.is_empty() {
+                format!("/{}", file)
+            } else {
+                format!("/{}/{}", directory, file)
+            };
+            owners.push((path, package.clone()));
+        }
+    }
+
+    owners
+}
+
+/// Split `version-release` at the last `-` if what follows starts with `r`
+/// and a digit, as in apk's `1.36.1-r2` and portage's `1.9.15_p5-r1`
+fn split_revision(version: &str) -> (String, String) {
+    match version.rsplit_once('-') {
+        Some((version, release))
+            if release.starts_with('r')
+                && release[1..]
+                    .chars()
+                    .next()
+                    .is_some_and(|c| c.is_ascii_digit()) =>
+        {
+            (version.to_string(), release.to_string())
+        }
+        _ => (version.to_string(), String::new()),
+    }
+}
+
+/// Parse apk's `installed` database into its packages
+pub(super) fn parse_apk_packages(content: &str) -> Vec<Application> {
+    let mut packages = Vec::new();
+    let mut package = Application::default();
+
+    // Records are separated by blank lines; the last may not end in one
+    for line in content.lines().chain(std::iter::once("")) {
+        let Some((key, value)) = line.split_once(':') else {
+            if !package.name.is_empty() {
+                packages.push(std::mem::take(&mut package));
+            }
+            package = Application::default();
+            continue;
+        };
+        match key {
+            "P" => {
+                package.name = value.to_string();
+                package.display_name = value.to_string();
+            }
+            "V" => (package.version, package.release) = split_revision(value),
+            "A" => package.arch = value.to_string(),
+            "T" => package.description = value.to_string(),
+            "U" => package.url = value.to_string(),
+            "m" => package.publisher = value.to_string(),
+            "o" => package.source = value.to_string(),
+            _ => {}
+        }
+    }
+
+    packages
+}
+
+/// Parse a pacman `desc` database entry
+pub(super) fn parse_pacman_desc(desc: &str) -> Option<Application> {
+    let mut package = Application::default();
+    let mut lines = desc.lines();
+    while let Some(section) = lines.next() {
+        let Some(value) = lines.next() else {
+            break;
+        };
+        if value.is_empty() {
+            continue;
+        }
+        match section {
+            "%NAME%" => {
+                package.name = value.to_string();
+                package.display_name = value.to_string();
+            }
+            "%VERSION%" => {
+                // [epoch:]pkgver-pkgrel
+                let (epoch, version) = match value.split_once(':') {
+                    Some((epoch, version)) => (epoch.parse().unwrap_or(0), version),
+                    None => (0, value),
+                };
+                let (version, release) = version.rsplit_once('-').unwrap_or((version, ""));
+                package.epoch = epoch;
+                package.version = version.to_string();
+                package.release = release.to_string();
+            }
+            "%ARCH%" => package.arch = value.to_string(),
+            "%DESC%" => package.description = value.to_string(),
+            "%URL%" => package.url = value.to_string(),
+            "%PACKAGER%" => package.publisher = value.to_string(),
+            _ => {}
+        }
+        // Skip the rest of a multi-line section
+        for line in lines.by_ref() {
+            if line.is_empty() {
+                break;
+            }
+        }
+    }
+
+    (!package.name.is_empty()).then_some(package)
+}
+
+/// Parse a dpkg `status` file, or a distroless `status.d` entry, into the
+/// installed packages
+///
+/// Entries without a `Status` field, as in `status.d`, count as installed.
+pub(super) fn parse_dpkg_status(content: &str) -> Vec<Application> {
+    let mut packages = Vec::new();
+    let mut package = Application::default();
+    let mut installed = true;
+
+    for line in content.lines().chain(std::iter::once("")) {
+        if line.is_empty() {
+            if installed && !package.name.is_empty() {
+                packages.push(std::mem::take(&mut package));
+            }
+            package = Application::default();
+            installed = true;
+            continue;
+        }
+        // Continuation lines of multi-line fields
+        if line.starts_with([' ', '\t']) {
+            continue;
+        }
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        match key {
+            "Package" => {
+                package.name = value.to_string();
+                package.display_name = value.to_string();
+            }
+            "Status" => installed = value.ends_with(" installed"),
+            // `Source: name (version)` when the source version differs
+            "Source" => {
+                package.source = value.split(' ').next().unwrap_or_default().to_string();
+            }
+            "Version" => {
+                // [epoch:]upstream[-revision]
+                let (epoch, version) = match value.split_once(':') {
+                    Some((epoch, version)) => (epoch.parse().unwrap_or(0), version),
+                    None => (0, value),
+                };
+                let (version, release) = version.rsplit_once('-').unwrap_or((version, ""));
+                package.epoch = epoch;
+                package.version = version.to_string();
+                package.release = release.to_string();
+            }
+            "Architecture" => package.arch = value.to_string(),
+            "Maintainer" => package.publisher = value.to_string(),
+            "Homepage" => package.url = value.to_string(),
+            "Description" => package.description = value.to_string(),
+            _ => {}
+        }
+    }
+
+    packages
+}
+
+/// `rpm -qa` output in [`RPM_QUERY_FORMAT`] into the installed packages
+fn parse_rpm_query(output: &str) -> Vec<Application> {
+    output
+        .lines()
+        .filter_map(|line| {
+            let fields: Vec<&str> = line.split('\t').collect();
+            let [name, epoch, version, release, arch, source_rpm] = fields[..] else {
+                return None;
+            };
+            // name-version-release.src.rpm, or (none) for gpg-pubkey
+            let source = source_rpm
+                .strip_suffix(".src.rpm")
+                .and_then(|nvr| nvr.rsplitn(3, '-').nth(2))
+                .unwrap_or_default();
+            Some(Application {
+                name: name.to_string(),
+                display_name: name.to_string(),
+                epoch: epoch.parse().unwrap_or(0),
+                version: version.to_string(),
+                release: release.to_string(),
+                arch: arch.to_string(),
+                source: source.to_string(),
+                ..Default::default()
+            })
+        })
+        .collect()
+}
+
+/// Parse a portage database entry, `<category>/<name>-<version>[-r<n>]`
+fn parse_portage_entry(category: &str, entry: &str) -> Option<Application> {
+    let (rest, release) = split_revision(entry);
+    let (name, version) = rest.rsplit_once('-')?;
+    if !version.starts_with(|c: char| c.is_ascii_digit()) || name.is_empty() {
+        return None;
+    }
+    Some(Application {
+        name: name.to_string(),
+        display_name: format!("{}/{}", category, name),
+        version: version.to_string(),
+        release,
+        install_path: format!("{}/{}/{}", PORTAGE_DB, category, entry),
+        ..Default::default()
+    })
+}
+
+/// Parse a portage `CONTENTS` file into the paths it installed
+fn parse_portage_contents(contents: &str) -> Vec<String> {
+    contents
+        .lines()
+        .filter_map(|line| {
+            // `dir <path>`, `obj <path> <md5> <mtime>`, `sym <path> -> <target> <mtime>`;
+            // paths may hold spaces
+            let (kind, rest) = line.split_once(' ')?;
+            let path = match kind {
+                "dir" => rest,
+                "obj" => rest.rsplitn(3, ' ').nth(2)?,
+                "sym" => rest.split_once(" -> ")?.0,
+                _ => return None,
+            };
+            Some(path.to_string())
+        })
+        .collect()
+}
 
 impl Guestfs {
+    /// List Debian packages from dpkg's status file, with full versions
+    pub fn dpkg_applications(&mut self) -> Result<Vec<Application>> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: dpkg_applications");
+        }
+
+        if !self.exists(DPKG_STATUS)? {
+            return Ok(Vec::new());
+        }
+        Ok(parse_dpkg_status(&self.cat(DPKG_STATUS)?))
+    }
+
+    /// List RPM packages with full versions, querying the guest's RPM database
+    pub fn rpm_applications(&mut self) -> Result<Vec<Application>> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: rpm_applications");
+        }
+
+        if !self.exists("/var/lib/rpm")? {
+            return Ok(Vec::new());
+        }
+        match self.command(&["rpm", "-qa", "--queryformat", RPM_QUERY_FORMAT]) {
+            Ok(output) => Ok(parse_rpm_query(&output)),
+            Err(_) => Ok(Vec::new()),
+        }
+    }
+
+    /// List Alpine packages from apk's database
+    pub fn apk_list(&mut self) -> Result<Vec<Application>> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: apk_list");
+        }
+
+        if !self.exists(APK_INSTALLED)? {
+            return Ok(Vec::new());
+        }
+        Ok(parse_apk_packages(&self.cat(APK_INSTALLED)?))
+    }
+
+    /// List Arch packages from pacman's local database
+    pub fn pacman_list(&mut self) -> Result<Vec<Application>> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: pacman_list");
+        }
+
+        if !self.exists(PACMAN_LOCAL)? {
+            return Ok(Vec::new());
+        }
+
+        let mut packages = Vec::new();
+        for entry in self.ls(PACMAN_LOCAL)? {
+            let dir = format!("{}/{}", PACMAN_LOCAL, entry);
+            let Ok(desc) = self.cat(&format!("{}/desc", dir)) else {
+                continue;
+            };
+            if let Some(mut package) = parse_pacman_desc(&desc) {
+                package.install_path = dir;
+                packages.push(package);
+            }
+        }
+        packages.sort_by(|a, b| a.name.cmp(&b.name));
+
+        Ok(packages)
+    }
+
+    /// List Gentoo packages from portage's installed package database
+    pub fn portage_list(&mut self) -> Result<Vec<Application>> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: portage_list");
+        }
+
+        if !self.exists(PORTAGE_DB)? {
+            return Ok(Vec::new());
+        }
+
+        let mut packages = Vec::new();
+        for category in self.ls(PORTAGE_DB)? {
+            let Ok(entries) = self.ls(&format!("{}/{}", PORTAGE_DB, category)) else {
+                continue;
+            };
+            for entry in entries {
+                // Merges in progress are kept in -MERGING- entries
+                if entry.starts_with('-') {
+                    continue;
+                }
+                let Some(mut package) = parse_portage_entry(&category, &entry) else {
+                    continue;
+                };
+                let dir = package.install_path.clone();
+                if let Ok(description) = self.cat(&format!("{}/DESCRIPTION", dir)) {
+                    package.description = description.trim().to_string();
+                }
+                if let Ok(homepage) = self.cat(&format!("{}/HOMEPAGE", dir)) {
+                    package.url = homepage.split_whitespace().next().unwrap_or("").to_string();
+                }
+                if let Ok(chost) = self.cat(&format!("{}/CHOST", dir)) {
+                    package.arch = chost.split('-').next().unwrap_or("").trim().to_string();
+                }
+                packages.push(package);
+            }
+        }
+        packages.sort_by(|a, b| a.display_name.cmp(&b.display_name));
+
+        Ok(packages)
+    }
+
     /// List Debian packages
     ///
-    pub fn dpkg_list(&mut self) -> Result<Vec<String>> {
+    pub(crate) fn dpkg_list_untraced(&mut self) -> Result<Vec<String>> {
         self.ensure_ready()?;
 
         if self.verbose {
@@ -46,7 +435,7 @@ impl Guestfs {
 
     /// List RPM packages
     ///
-    pub fn rpm_list(&mut self) -> Result<Vec<String>> {
+    pub(crate) fn rpm_list_untraced(&mut self) -> Result<Vec<String>> {
         self.ensure_ready()?;
 
         if self.verbose {
@@ -164,6 +553,89 @@ impl Guestfs {
 
         Err(Error::NotFound(format!("Package {} not found", package)))
     }
+
+    /// Build an index mapping every packaged file path to its owning package
+    ///
+    /// Reads the dpkg, rpm, pacman, apk and portage databases, whichever are
+    /// present.
+    pub fn package_file_owners(&mut self) -> Result<HashMap<String, String>> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: package_file_owners");
+        }
+
+        let mut owners = HashMap::new();
+
+        // dpkg: one <package>[:<arch>].list per package
+        if self.exists("/var/lib/dpkg/info")? {
+            for entry in self.ls("/var/lib/dpkg/info")? {
+                let Some(stem) = entry.strip_suffix(".list") else {
+                    continue;
+                };
+                let package = stem.split(':').next().unwrap_or(stem).to_string();
+                let list_file = format!("/var/lib/dpkg/info/{}", entry);
+                if let Ok(content) = self.cat(&list_file) {
+                    for line in content.lines().filter(|l| l.starts_with('/')) {
+                        owners
+                            .entry(line.to_string())
+                            .or_insert_with(|| package.clone());
+                    }
+                }
+            }
+        }
+
+        // RPM: query the file list of every installed package
+        if self.exists("/var/lib/rpm")? {
+            if let Ok(output) =
+                self.command(&["rpm", "-qa", "--qf", "[%{FILENAMES}\\t%{NAME}\\n]"])
+            {
+                for line in output.lines() {
+                    if let Some((path, package)) = line.split_once('\t') {
+                        owners
+                            .entry(path.to_string())
+                            .or_insert_with(|| package.to_string());
+                    }
+                }
+            }
+        }
+
+        // pacman: local/<name>-<version>/{desc,files}
+        if self.exists(PACMAN_LOCAL)? {
+            for entry in self.ls(PACMAN_LOCAL)? {
+                let dir = format!("{}/{}", PACMAN_LOCAL, entry);
+                let (Ok(desc), Ok(files)) = (
+                    self.cat(&format!("{}/desc", dir)),
+                    self.cat(&format!("{}/files", dir)),
+                ) else {
+                    continue;
+                };
+                for (path, package) in parse_pacman_entry(&desc, &files) {
+                    owners.entry(path).or_insert(package);
+                }
+            }
+        }
+
+        // apk: single installed database
+        if self.exists(APK_INSTALLED)? {
+            let content = self.cat(APK_INSTALLED)?;
+            for (path, package) in parse_apk_installed(&content) {
+                owners.entry(path).or_insert(package);
+            }
+        }
+
+        // portage: <category>/<name>-<version>/CONTENTS
+        for package in self.portage_list()? {
+            let Ok(contents) = self.cat(&format!("{}/CONTENTS", package.install_path)) else {
+                continue;
+            };
+            for path in parse_portage_contents(&contents) {
+                owners.entry(path).or_insert_with(|| package.name.clone());
+            }
+        }
+
+        Ok(owners)
+    }
 }
 
 #[cfg(test)]
@@ -175,4 +647,133 @@ mod tests {
         let mut g = Guestfs::new().unwrap();
         // API structure tests
     }
+
+    #[test]
+    fn test_parse_pacman_entry() {
+        let desc = "%NAME%\nsudo\n\n%VERSION%\n1.9.15-1\n";
+        let files = "%FILES%\nusr/\nusr/bin/\nusr/bin/sudo\n\n%BACKUP%\netc/sudoers\n";
+        let owners = parse_pacman_entry(desc, files);
+        assert_eq!(owners.len(), 3);
+        assert!(owners.contains(&("/usr/bin/sudo".to_string(), "sudo".to_string())));
+        assert!(owners.contains(&("/usr/bin".to_string(), "sudo".to_string())));
+    }
+
+    #[test]
+    fn test_parse_apk_installed() {
+        let content = "P:busybox\nV:1.36\nF:bin\nR:busybox\n\nP:sudo\nF:usr/bin\nR:sudo\n";
+        let owners = parse_apk_installed(content);
+        assert!(owners.contains(&("/bin/busybox".to_string(), "busybox".to_string())));
+        assert!(owners.contains(&("/usr/bin/sudo".to_string(), "sudo".to_string())));
+        assert!(owners.contains(&("/usr/bin".to_string(), "sudo".to_string())));
+    }
+
+    #[test]
+    fn test_split_revision() {
+        assert_eq!(
+            split_revision("1.36.1-r2"),
+            ("1.36.1".to_string(), "r2".to_string())
+        );
+        assert_eq!(
+            split_revision("2024.1-rc1"),
+            ("2024.1-rc1".to_string(), String::new())
+        );
+    }
+
+    #[test]
+    fn test_parse_apk_packages() {
+        let content = "C:Q1abc=\nP:musl\nV:1.2.4-r2\nA:x86_64\nT:the musl c library\n\
+                       U:https://musl.libc.org/\nF:lib\nR:libc.musl-x86_64.so.1\n\n\
+                       P:busybox\nV:1.36.1-r15\nA:x86_64\n\n\
+                       P:busybox-binsh\nV:1.36.1-r15\nA:x86_64\no:busybox\n";
+        let packages = parse_apk_packages(content);
+        assert_eq!(packages.len(), 3);
+        assert_eq!(packages[0].name, "musl");
+        assert_eq!(packages[0].version, "1.2.4");
+        assert_eq!(packages[0].release, "r2");
+        assert_eq!(packages[0].url, "https://musl.libc.org/");
+        assert_eq!(packages[1].name, "busybox");
+        assert_eq!(packages[1].arch, "x86_64");
+        assert_eq!(packages[1].source, "");
+        assert_eq!(packages[2].name, "busybox-binsh");
+        assert_eq!(packages[2].source, "busybox");
+    }
+
+    #[test]
+    fn test_parse_dpkg_status() {
+        let status = "Package: libc6\nStatus: install ok installed\nArchitecture: amd64\n\
+                      Version: 2.36-9+deb12u4\nDescription: GNU C Library\n Shared libraries.\n\n\
+                      Package: removed\nStatus: deinstall ok config-files\nVersion: 1.0\n\n\
+                      Package: tzdata\nVersion: 1:2024a-0+deb12u1\nArchitecture: all\n\n\
+                      Package: libssl3\nSource: openssl (3.0.11-1~deb12u2)\n\
+                      Version: 3.0.11-1~deb12u2+b1\n";
+        let packages = parse_dpkg_status(status);
+        assert_eq!(packages.len(), 3);
+        assert_eq!(packages[0].name, "libc6");
+        assert_eq!(packages[0].source, "");
+        assert_eq!(packages[0].version, "2.36");
+        assert_eq!(packages[0].release, "9+deb12u4");
+        assert_eq!(packages[0].description, "GNU C Library");
+        assert_eq!(packages[1].name, "tzdata");
+        assert_eq!(packages[1].epoch, 1);
+        assert_eq!(packages[1].full_version(), "1:2024a-0+deb12u1");
+        assert_eq!(packages[2].source, "openssl");
+    }
+
+    #[test]
+    fn test_parse_rpm_query() {
+        let output = "openssl-libs\t1\t3.0.7\t25.el9_3\tx86_64\topenssl-3.0.7-25.el9_3.src.rpm\n\
+                      gpg-pubkey\t0\tfd431d51\t4ae0493b\t(none)\t(none)\n\
+                      truncated\t0\n";
+        let packages = parse_rpm_query(output);
+        assert_eq!(packages.len(), 2);
+        assert_eq!(packages[0].name, "openssl-libs");
+        assert_eq!(packages[0].full_version(), "1:3.0.7-25.el9_3");
+        assert_eq!(packages[0].source, "openssl");
+        assert_eq!(packages[1].source, "");
+    }
+
+    #[test]
+    fn test_parse_pacman_desc() {
+        let desc = "%NAME%\nsudo\n\n%VERSION%\n1:1.9.15.p5-1\n\n%DESC%\nGive certain users \
+                    root access\n\n%ARCH%\nx86_64\n\n%LICENSE%\ncustom\nISC\n\n%URL%\n\
+                    https://www.sudo.ws/\n";
+        let package = parse_pacman_desc(desc).unwrap();
+        assert_eq!(package.name, "sudo");
+        assert_eq!(package.epoch, 1);
+        assert_eq!(package.version, "1.9.15.p5");
+        assert_eq!(package.release, "1");
+        assert_eq!(package.arch, "x86_64");
+        assert_eq!(package.url, "https://www.sudo.ws/");
+        assert!(parse_pacman_desc("%VERSION%\n1.0-1\n").is_none());
+    }
+
+    #[test]
+    fn test_parse_portage_entry() {
+        let package = parse_portage_entry("app-admin", "sudo-1.9.15_p5-r1").unwrap();
+        assert_eq!(package.name, "sudo");
+        assert_eq!(package.display_name, "app-admin/sudo");
+        assert_eq!(package.version, "1.9.15_p5");
+        assert_eq!(package.release, "r1");
+
+        let package = parse_portage_entry("media-fonts", "font-adobe-100dpi-1.0.4").unwrap();
+        assert_eq!(package.name, "font-adobe-100dpi");
+        assert_eq!(package.release, "");
+        assert!(parse_portage_entry("virtual", "libc").is_none());
+    }
+
+    #[test]
+    fn test_parse_portage_contents() {
+        let contents = "dir /usr\nobj /usr/bin/sudo 1f3870be274f6c49b3e31a0c6728957f 1700000000\n\
+                        obj /usr/share/doc/a b.txt 0cc175b9c0f1b6a831c399e269772661 1700000000\n\
+                        sym /usr/bin/sudoedit -> sudo 1700000000\n";
+        assert_eq!(
+            parse_portage_contents(contents),
+            [
+                "/usr",
+                "/usr/bin/sudo",
+                "/usr/share/doc/a b.txt",
+                "/usr/bin/sudoedit"
+            ]
+        );
+    }
 }
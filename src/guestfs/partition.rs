@@ -3,9 +3,10 @@
 
 use crate::core::{Error, Result};
 use crate::guestfs::Guestfs;
+use serde::{Deserialize, Serialize};
 
 /// Partition information
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct PartInfo {
     pub part_num: i32,
     pub part_start: i64,
@@ -16,7 +17,7 @@ pub struct PartInfo {
 impl Guestfs {
     /// Get partition list
     ///
-    pub fn part_list(&self, device: &str) -> Result<Vec<PartInfo>> {
+    pub(crate) fn part_list_untraced(&self, device: &str) -> Result<Vec<PartInfo>> {
         self.ensure_ready()?;
 
         // Ensure it's a whole device
@@ -43,7 +44,7 @@ impl Guestfs {
 
     /// Get partition table type (mbr or gpt)
     ///
-    pub fn part_get_parttype(&self, device: &str) -> Result<String> {
+    pub(crate) fn part_get_parttype_untraced(&self, device: &str) -> Result<String> {
         self.ensure_ready()?;
 
         if !self.is_whole_device(device)? {
@@ -181,7 +182,7 @@ impl Guestfs {
 
     /// Get partition number from partition device
     ///
-    pub fn part_to_partnum(&self, partition: &str) -> Result<i32> {
+    pub(crate) fn part_to_partnum_untraced(&self, partition: &str) -> Result<i32> {
         // Extract partition number from device name
         // /dev/sda1 -> 1
         // /dev/vda2 -> 2
@@ -0,0 +1,241 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Bounded file reads for previews and content search
+//!
+//! Guest files can be arbitrarily large (multi-gigabyte logs, database
+//! files, core dumps). These helpers stream from the mounted filesystem and
+//! cap how much is held in memory: small files are read whole, larger ones
+//! as a head and tail window, and binary content is detected up front.
+
+use crate::core::{Error, Result};
+use crate::guestfs::Guestfs;
+use std::fs::File;
+use std::io::{BufReader, Read, Seek, SeekFrom};
+
+/// Bytes examined when deciding whether content is binary
+pub const BINARY_SNIFF_BYTES: usize = 8192;
+
+/// Default preview limit used by interactive views
+pub const DEFAULT_PREVIEW_BYTES: u64 = 1024 * 1024;
+
+/// Whether a sample of file content looks binary
+///
+/// Content is binary if it contains a NUL byte or invalid UTF-8 within the
+/// first [`BINARY_SNIFF_BYTES`]. A multi-byte character cut off at the end
+/// of the sample does not count.
+pub fn is_binary(sample: &[u8]) -> bool {
+    let sample = &sample[..sample.len().min(BINARY_SNIFF_BYTES)];
+    if sample.contains(&0) {
+        return true;
+    }
+    match std::str::from_utf8(sample) {
+        Ok(_) => false,
+        Err(e) => e.error_len().is_some(),
+    }
+}
+
+/// Bounded view of a file's content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct FilePreview {
+    /// Size of the whole file in bytes
+    pub size: u64,
+    /// Content from the start of the file (the whole file if it fits)
+    pub head: Vec<u8>,
+    /// Content from the end of the file; empty unless the file was truncated
+    pub tail: Vec<u8>,
+    /// Content looks binary
+    pub binary: bool,
+}
+
+impl FilePreview {
+    /// Whether part of the file was left out
+    pub fn is_truncated(&self) -> bool {
+        self.omitted_bytes() > 0
+    }
+
+    /// Bytes between the head and tail windows that were not read
+    pub fn omitted_bytes(&self) -> u64 {
+        self.size
+            .saturating_sub(self.head.len() as u64 + self.tail.len() as u64)
+    }
+
+    /// Head window as text (lossy for invalid UTF-8)
+    pub fn head_text(&self) -> String {
+        String::from_utf8_lossy(&self.head).into_owned()
+    }
+
+    /// Tail window as text (lossy for invalid UTF-8)
+    pub fn tail_text(&self) -> String {
+        String::from_utf8_lossy(&self.tail).into_owned()
+    }
+}
+
+/// Trim a head window back to the last complete line
+fn trim_head(mut head: Vec<u8>) -> Vec<u8> {
+    if let Some(pos) = head.iter().rposition(|&b| b == b'\n') {
+        head.truncate(pos + 1);
+    }
+    head
+}
+
+/// Trim a tail window forward to the first complete line
+fn trim_tail(tail: Vec<u8>) -> Vec<u8> {
+    match tail.iter().position(|&b| b == b'\n') {
+        Some(pos) if pos + 1 < tail.len() => tail[pos + 1..].to_vec(),
+        _ => tail,
+    }
+}
+
+/// Build a preview from an open reader of known size
+pub fn preview_reader<R: Read + Seek>(
+    reader: &mut R,
+    size: u64,
+    max_bytes: u64,
+) -> Result<FilePreview> {
+    if max_bytes == 0 || size <= max_bytes {
+        let mut head = Vec::with_capacity(size as usize);
+        reader
+            .by_ref()
+            .take(size)
+            .read_to_end(&mut head)
+            .map_err(Error::Io)?;
+        let binary = is_binary(&head);
+        return Ok(FilePreview {
+            size,
+            head,
+            tail: Vec::new(),
+            binary,
+        });
+    }
+
+    let window = max_bytes / 2;
+    let mut head = Vec::with_capacity(window as usize);
+    reader
+        .by_ref()
+        .take(window)
+        .read_to_end(&mut head)
+        .map_err(Error::Io)?;
+    let binary = is_binary(&head);
+
+    let mut tail = Vec::with_capacity(window as usize);
+    reader
+        .seek(SeekFrom::Start(size - window))
+        .map_err(Error::Io)?;
+    reader
+        .by_ref()
+        .take(window)
+        .read_to_end(&mut tail)
+        .map_err(Error::Io)?;
+
+    // Line-align text windows so neither starts or ends mid-line
+    let (head, tail) = if binary {
+        (head, tail)
+    } else {
+        (trim_head(head), trim_tail(tail))
+    };
+
+    Ok(FilePreview {
+        size,
+        head,
+        tail,
+        binary,
+    })
+}
+
+impl Guestfs {
+    /// Open a file for streaming reads
+    ///
+    pub fn open_file(&mut self, path: &str) -> Result<BufReader<File>> {
+        self.ensure_ready()?;
+
+        if self.trace {
+            eprintln!("guestfs: open_file {}", path);
+        }
+
+        let host_path = self.resolve_guest_path(path)?;
+        let file = File::open(&host_path)
+            .map_err(|e| Error::NotFound(format!("Failed to open {}: {}", path, e)))?;
+        Ok(BufReader::new(file))
+    }
+
+    /// Read at most `max_bytes` of a file as a head and tail window
+    ///
+    /// Files up to `max_bytes` are read whole; `0` disables the limit.
+    pub fn preview_file(&mut self, path: &str, max_bytes: u64) -> Result<FilePreview> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: preview_file {} {}", path, max_bytes);
+        }
+
+        let host_path = self.resolve_guest_path(path)?;
+        let mut file = File::open(&host_path)
+            .map_err(|e| Error::NotFound(format!("Failed to open {}: {}", path, e)))?;
+        let size = file.metadata().map_err(Error::Io)?.len();
+        preview_reader(&mut file, size, max_bytes)
+    }
+
+    /// Whether a file's content looks binary, reading only its first block
+    ///
+    pub fn is_binary_file(&mut self, path: &str) -> Result<bool> {
+        let mut sample = Vec::with_capacity(BINARY_SNIFF_BYTES);
+        self.open_file(path)?
+            .take(BINARY_SNIFF_BYTES as u64)
+            .read_to_end(&mut sample)
+            .map_err(Error::Io)?;
+        Ok(is_binary(&sample))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Cursor;
+
+    fn preview(content: &[u8], max_bytes: u64) -> FilePreview {
+        preview_reader(&mut Cursor::new(content), content.len() as u64, max_bytes).unwrap()
+    }
+
+    #[test]
+    fn test_is_binary() {
+        assert!(!is_binary(b"plain text\n"));
+        assert!(!is_binary("überserver-東京\n".as_bytes()));
+        assert!(is_binary(b"\x7fELF\x02\x01\x01\x00\x00"));
+        assert!(is_binary(b"abc\xff\xfedef"));
+        // Multi-byte character cut off by the sample boundary
+        assert!(!is_binary(&"ab東".as_bytes()[..4]));
+    }
+
+    #[test]
+    fn test_small_file_read_whole() {
+        let p = preview(b"one\ntwo\n", 1024);
+        assert_eq!(p.head, b"one\ntwo\n");
+        assert!(p.tail.is_empty());
+        assert!(!p.is_truncated());
+        assert!(!p.binary);
+    }
+
+    #[test]
+    fn test_large_file_head_and_tail() {
+        let content: String = (0..1000).map(|i| format!("line {}\n", i)).collect();
+        let p = preview(content.as_bytes(), 200);
+
+        assert!(p.is_truncated());
+        assert!(p.head.len() <= 100 && p.tail.len() <= 100);
+        assert!(p.head_text().starts_with("line 0\n"));
+        assert!(p.head_text().ends_with('\n'));
+        assert!(p.tail_text().starts_with("line "));
+        assert!(p.tail_text().ends_with("line 999\n"));
+        assert_eq!(
+            p.omitted_bytes(),
+            content.len() as u64 - p.head.len() as u64 - p.tail.len() as u64
+        );
+    }
+
+    #[test]
+    fn test_unlimited() {
+        let content = vec![b'x'; 4096];
+        let p = preview(&content, 0);
+        assert_eq!(p.head.len(), 4096);
+        assert!(!p.is_truncated());
+    }
+}
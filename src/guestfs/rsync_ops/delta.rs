@@ -0,0 +1,328 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Rolling-checksum delta transfer, applied to the target in place
+//!
+//! The target is cut into fixed blocks, each indexed by an Adler-style weak
+//! checksum and a SHA-256. The source is scanned a byte at a time with the
+//! weak checksum rolled along; a window that matches a target block is not
+//! sent, everything else is. Since the target is rewritten where it lies, a
+//! block may only be taken from at or after the current offset, where it
+//! has not been overwritten yet, and unmatched data is compared against
+//! what is already there so unchanged ranges are not written.
+
+use crate::core::Result;
+use sha2::{Digest, Sha256};
+use std::collections::HashMap;
+use std::fs::File;
+use std::io::{self, Read};
+use std::os::unix::fs::FileExt;
+
+const MIN_BLOCK_SIZE: usize = 4096;
+const MAX_BLOCK_SIZE: usize = 128 * 1024;
+const READ_SIZE: usize = 256 * 1024;
+/// Unmatched data is written out once this much has built up
+const FLUSH_SIZE: usize = 1024 * 1024;
+/// Granularity at which unmatched data is compared with the target
+const COMPARE_SIZE: usize = 4096;
+
+/// Block size for a target of `len` bytes, about its square root
+pub(super) fn block_size(len: u64) -> usize {
+    ((len as f64).sqrt() as usize).clamp(MIN_BLOCK_SIZE, MAX_BLOCK_SIZE)
+}
+
+/// Weak checksum of a window that can slide one byte at a time
+#[derive(Debug, Clone, Copy)]
+pub(super) struct Rolling {
+    a: u32,
+    b: u32,
+    len: u32,
+}
+
+impl Rolling {
+    pub fn new(window: &[u8]) -> Self {
+        let len = window.len() as u32;
+        let (mut a, mut b) = (0u32, 0u32);
+        for (i, &byte) in window.iter().enumerate() {
+            a = a.wrapping_add(byte.into());
+            b = b.wrapping_add((len - i as u32).wrapping_mul(byte.into()));
+        }
+        Self { a, b, len }
+    }
+
+    /// Slide the window past `out` and over `into`
+    pub fn roll(&mut self, out: u8, into: u8) {
+        self.a = self.a.wrapping_sub(out.into()).wrapping_add(into.into());
+        self.b = self
+            .b
+            .wrapping_sub(self.len.wrapping_mul(out.into()))
+            .wrapping_add(self.a);
+    }
+
+    pub fn digest(&self) -> u32 {
+        (self.a & 0xffff) | (self.b << 16)
+    }
+}
+
+/// Checksums of a target's whole blocks
+pub(super) struct Signature {
+    block_size: usize,
+    blocks: HashMap<u32, Vec<(u64, [u8; 32])>>,
+}
+
+impl Signature {
+    pub fn new(target: &File) -> Result<Self> {
+        let len = target.metadata()?.len();
+        let block_size = block_size(len);
+        let mut blocks: HashMap<u32, Vec<_>> = HashMap::new();
+        let mut block = vec![0; block_size];
+        let mut offset = 0;
+        while offset + block_size as u64 <= len {
+            target.read_exact_at(&mut block, offset)?;
+            blocks
+                .entry(Rolling::new(&block).digest())
+                .or_default()
+                .push((offset, Sha256::digest(&block).into()));
+            offset += block_size as u64;
+        }
+        Ok(Self { block_size, blocks })
+    }
+
+    /// Offset of a block at or after `from` holding `window`, preferring
+    /// `from` itself
+    fn find(&self, weak: u32, window: &[u8], from: u64) -> Option<u64> {
+        let candidates = self.blocks.get(&weak)?;
+        let strong: [u8; 32] = Sha256::digest(window).into();
+        let mut found = None;
+        for &(offset, hash) in candidates {
+            if offset < from || hash != strong {
+                continue;
+            }
+            if offset == from {
+                return Some(offset);
+            }
+            found = found.or(Some(offset));
+        }
+        found
+    }
+}
+
+/// Bytes written to and kept in the target
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(super) struct Delta {
+    pub written: u64,
+    pub matched: u64,
+}
+
+/// Make `target`, described by `signature`, hold what `source` reads
+pub(super) fn update_in_place<R: Read>(
+    mut source: R,
+    target: &File,
+    signature: &Signature,
+) -> Result<Delta> {
+    let block = signature.block_size;
+    let mut delta = Delta::default();
+    // Source data from `base`; what is before `literal` has been written,
+    // and the window starts at `pos`
+    let mut buf = Vec::new();
+    let mut base = 0u64;
+    let mut literal = 0;
+    let mut pos = 0;
+    let mut eof = false;
+    let mut rolling: Option<Rolling> = None;
+
+    loop {
+        while !eof && buf.len() < pos + block {
+            eof = fill(&mut source, &mut buf)?;
+        }
+        if buf.len() < pos + block {
+            break;
+        }
+
+        let window = &buf[pos..pos + block];
+        let weak = rolling.get_or_insert_with(|| Rolling::new(window)).digest();
+        let offset = base + pos as u64;
+        if let Some(found) = signature.find(weak, window, offset) {
+            write_changed(
+                target,
+                &buf[literal..pos],
+                base + literal as u64,
+                &mut delta,
+            )?;
+            if found == offset {
+                delta.matched += block as u64;
+            } else {
+                let mut moved = vec![0; block];
+                target.read_exact_at(&mut moved, found)?;
+                write_changed(target, &moved, offset, &mut delta)?;
+            }
+            pos += block;
+            literal = pos;
+            rolling = None;
+        } else {
+            while !eof && buf.len() <= pos + block {
+                eof = fill(&mut source, &mut buf)?;
+            }
+            if buf.len() <= pos + block {
+                break;
+            }
+            if let Some(rolling) = rolling.as_mut() {
+                rolling.roll(buf[pos], buf[pos + block]);
+            }
+            pos += 1;
+            if pos - literal >= FLUSH_SIZE {
+                write_changed(
+                    target,
+                    &buf[literal..pos],
+                    base + literal as u64,
+                    &mut delta,
+                )?;
+                literal = pos;
+            }
+        }
+
+        if literal >= FLUSH_SIZE {
+            buf.drain(..literal);
+            base += literal as u64;
+            pos -= literal;
+            literal = 0;
+        }
+    }
+
+    write_changed(target, &buf[literal..], base + literal as u64, &mut delta)?;
+    let len = base + buf.len() as u64;
+    if target.metadata()?.len() != len {
+        target.set_len(len)?;
+    }
+    Ok(delta)
+}
+
+/// Read another chunk onto `buf`, returning whether the source has ended
+fn fill<R: Read>(source: &mut R, buf: &mut Vec<u8>) -> io::Result<bool> {
+    let start = buf.len();
+    buf.resize(start + READ_SIZE, 0);
+    let read = loop {
+        match source.read(&mut buf[start..]) {
+            Ok(read) => break read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => {
+                buf.truncate(start);
+                return Err(e);
+            }
+        }
+    };
+    buf.truncate(start + read);
+    Ok(read == 0)
+}
+
+/// Write the parts of `data` that differ from the target at `offset`
+fn write_changed(target: &File, data: &[u8], offset: u64, delta: &mut Delta) -> Result<()> {
+    let mut existing = [0; COMPARE_SIZE];
+    let mut offset = offset;
+    for chunk in data.chunks(COMPARE_SIZE) {
+        let have = read_up_to(target, &mut existing[..chunk.len()], offset)?;
+        if have == chunk.len() && existing[..have] == *chunk {
+            delta.matched += chunk.len() as u64;
+        } else {
+            target.write_all_at(chunk, offset)?;
+            delta.written += chunk.len() as u64;
+        }
+        offset += chunk.len() as u64;
+    }
+    Ok(())
+}
+
+/// Fill `buf` from `offset`, short only at the end of the file
+fn read_up_to(file: &File, buf: &mut [u8], offset: u64) -> io::Result<usize> {
+    let mut filled = 0;
+    while filled < buf.len() {
+        match file.read_at(&mut buf[filled..], offset + filled as u64) {
+            Ok(0) => break,
+            Ok(read) => filled += read,
+            Err(e) if e.kind() == io::ErrorKind::Interrupted => continue,
+            Err(e) => return Err(e),
+        }
+    }
+    Ok(filled)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::fs::OpenOptions;
+
+    /// Pseudo-random bytes, so blocks do not repeat
+    fn noise(len: usize, seed: u32) -> Vec<u8> {
+        let mut state = seed;
+        (0..len)
+            .map(|_| {
+                state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+                (state >> 16) as u8
+            })
+            .collect()
+    }
+
+    fn sync(old: &[u8], new: &[u8]) -> (Vec<u8>, Delta) {
+        let tmp = tempfile::tempdir().unwrap();
+        let path = tmp.path().join("target");
+        std::fs::write(&path, old).unwrap();
+        let target = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .open(&path)
+            .unwrap();
+        let signature = Signature::new(&target).unwrap();
+        let delta = update_in_place(new, &target, &signature).unwrap();
+        (std::fs::read(&path).unwrap(), delta)
+    }
+
+    #[test]
+    fn test_rolling() {
+        let data = noise(64, 1);
+        let mut rolling = Rolling::new(&data[..16]);
+        for start in 1..=48 {
+            rolling.roll(data[start - 1], data[start + 15]);
+            assert_eq!(
+                rolling.digest(),
+                Rolling::new(&data[start..start + 16]).digest()
+            );
+        }
+    }
+
+    #[test]
+    fn test_block_size() {
+        assert_eq!(block_size(0), MIN_BLOCK_SIZE);
+        assert_eq!(block_size(1 << 30), 32 * 1024);
+        assert_eq!(block_size(1 << 40), MAX_BLOCK_SIZE);
+    }
+
+    #[test]
+    fn test_update_in_place() {
+        let old = noise(3 * 1024 * 1024, 7);
+
+        let (synced, delta) = sync(&old, &old);
+        assert_eq!(synced, old);
+        assert_eq!(delta.written, 0);
+
+        // A few changed bytes cost a compare chunk
+        let mut new = old.clone();
+        new[100_000] ^= 0xff;
+        let (synced, delta) = sync(&old, &new);
+        assert_eq!(synced, new);
+        assert_eq!(delta.written, COMPARE_SIZE as u64);
+
+        // Removing bytes moves the blocks after them up
+        let mut new = old.clone();
+        new.drain(200_000..201_000);
+        new.truncate(2 * 1024 * 1024);
+        let (synced, delta) = sync(&old, &new);
+        assert_eq!(synced, new);
+        assert!(delta.matched >= 196_608);
+
+        let new = noise(10_000, 11);
+        let (synced, delta) = sync(&old, &new);
+        assert_eq!(synced, new);
+        assert_eq!(delta.written, 10_000);
+
+        let (synced, _) = sync(b"", b"guest\n");
+        assert_eq!(synced, b"guest\n");
+    }
+}
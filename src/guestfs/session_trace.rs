@@ -0,0 +1,500 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Record and replay of guestfs sessions
+//!
+//! Meant for bug reports about images that cannot be shared. While
+//! recording, the inspection calls made on any handle are kept in a
+//! [`SessionTrace`] with what they returned. Handles created while a trace
+//! is replayed answer the same calls from it without touching a disk, so
+//! the code built on those calls (reports, output formats) runs as it did
+//! on the reporter's machine.
+//!
+//! Traces hold the arguments and results of the calls: device names,
+//! partition and filesystem metadata, OS details, package and account
+//! lists. They never hold file contents or disk data. Calls made inside
+//! another traced call are not recorded, since replay answers the outer
+//! one.
+
+use super::inspect_enhanced::{
+    Certificate, LVMInfo, NetworkInterface, SystemService, UserAccount, WindowsApplication,
+    WindowsEventLogEntry, WindowsNetworkAdapter, WindowsService, WindowsSystemInfo, WindowsUpdate,
+};
+use super::luks::LuksInfo;
+use super::partition::PartInfo;
+use super::windows::WindowsScheduledTask;
+use crate::core::{Error, Result};
+use crate::guestfs::Guestfs;
+use serde::de::DeserializeOwned;
+use serde::{Deserialize, Serialize};
+use std::cell::Cell;
+use std::collections::{BTreeMap, HashMap};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex, RwLock};
+
+/// Version of the trace format, raised on incompatible changes
+pub const TRACE_VERSION: u32 = 1;
+
+/// Calls recorded by [`start_recording`]
+static RECORDING: Mutex<Option<SessionTrace>> = Mutex::new(None);
+static RECORDING_ACTIVE: AtomicBool = AtomicBool::new(false);
+
+/// Trace new handles replay (see [`set_replay`])
+static REPLAY: RwLock<Option<Arc<Replay>>> = RwLock::new(None);
+
+thread_local! {
+    /// Traced calls in progress on this thread
+    static DEPTH: Cell<usize> = const { Cell::new(0) };
+}
+
+/// A call and what it returned
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct TracedCall {
+    pub method: String,
+    pub args: Vec<String>,
+    /// Result of a successful call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub result: Option<serde_json::Value>,
+    /// Error message of a failed call
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Recorded guestfs session
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct SessionTrace {
+    pub version: u32,
+    pub guestkit_version: String,
+    /// RFC 3339 time recording started
+    pub recorded_at: String,
+    pub calls: Vec<TracedCall>,
+}
+
+impl SessionTrace {
+    fn new() -> Self {
+        Self {
+            version: TRACE_VERSION,
+            guestkit_version: crate::VERSION.to_string(),
+            recorded_at: chrono::Utc::now().to_rfc3339(),
+            calls: Vec::new(),
+        }
+    }
+
+    /// Parse a trace written by [`SessionTrace::to_json`]
+    pub fn from_json(json: &str) -> Result<Self> {
+        let trace: SessionTrace = serde_json::from_str(json)
+            .map_err(|e| Error::InvalidFormat(format!("Invalid session trace: {}", e)))?;
+        if trace.version != TRACE_VERSION {
+            return Err(Error::Unsupported(format!(
+                "session trace version {} (this version reads {})",
+                trace.version, TRACE_VERSION
+            )));
+        }
+        Ok(trace)
+    }
+
+    pub fn to_json(&self) -> Result<String> {
+        serde_json::to_string_pretty(self)
+            .map_err(|e| Error::Conversion(format!("Failed to serialize trace: {}", e)))
+    }
+}
+
+/// Start recording the calls of all handles
+pub fn start_recording() {
+    *RECORDING.lock().unwrap_or_else(|e| e.into_inner()) = Some(SessionTrace::new());
+    RECORDING_ACTIVE.store(true, Ordering::SeqCst);
+}
+
+/// Stop recording and return what was recorded
+pub fn finish_recording() -> Option<SessionTrace> {
+    RECORDING_ACTIVE.store(false, Ordering::SeqCst);
+    RECORDING.lock().unwrap_or_else(|e| e.into_inner()).take()
+}
+
+/// Have handles created from now on replay `trace`, or work on disks again
+pub fn set_replay(trace: Option<SessionTrace>) {
+    let mut replay = REPLAY.write().unwrap_or_else(|e| e.into_inner());
+    *replay = trace.map(|trace| Arc::new(Replay::new(trace)));
+}
+
+/// Trace that new handles replay
+pub(crate) fn current_replay() -> Option<Arc<Replay>> {
+    REPLAY.read().unwrap_or_else(|e| e.into_inner()).clone()
+}
+
+/// Answers calls from a trace
+///
+/// Calls are matched by method and arguments, so code that calls in
+/// another order still replays. A call recorded several times answers in
+/// recorded order, then repeats the last answer.
+#[derive(Debug)]
+pub struct Replay {
+    answers: Mutex<HashMap<CallKey, (Vec<TracedCall>, usize)>>,
+}
+
+/// Method and arguments of a call
+type CallKey = (String, Vec<String>);
+
+impl Replay {
+    fn new(trace: SessionTrace) -> Self {
+        let mut answers: HashMap<CallKey, (Vec<TracedCall>, usize)> = HashMap::new();
+        for call in trace.calls {
+            answers
+                .entry((call.method.clone(), call.args.clone()))
+                .or_default()
+                .0
+                .push(call);
+        }
+        Self {
+            answers: Mutex::new(answers),
+        }
+    }
+
+    fn answer<T: DeserializeOwned>(&self, method: &str, args: Vec<String>) -> Result<T> {
+        let mut answers = self.answers.lock().unwrap_or_else(|e| e.into_inner());
+        let key = (method.to_string(), args);
+        let Some((calls, next)) = answers.get_mut(&key) else {
+            return Err(Error::NotFound(format!(
+                "{}({}) is not in the session trace",
+                method,
+                key.1.join(", ")
+            )));
+        };
+        let call = &calls[(*next).min(calls.len() - 1)];
+        *next += 1;
+
+        if let Some(error) = &call.error {
+            return Err(Error::Replayed(error.clone()));
+        }
+        let result = call.result.clone().unwrap_or(serde_json::Value::Null);
+        serde_json::from_value(result).map_err(|e| {
+            Error::InvalidFormat(format!("Recorded result of {} does not fit: {}", method, e))
+        })
+    }
+}
+
+/// Decrements the call depth when a traced call returns or unwinds
+struct DepthGuard;
+
+impl Drop for DepthGuard {
+    fn drop(&mut self) {
+        DEPTH.with(|depth| depth.set(depth.get() - 1));
+    }
+}
+
+/// Run `call`, or answer it from the trace the handle replays
+fn traced<T: Serialize + DeserializeOwned>(
+    replay: Option<&Replay>,
+    method: &str,
+    args: Vec<String>,
+    call: impl FnOnce() -> Result<T>,
+) -> Result<T> {
+    if let Some(replay) = replay {
+        return replay.answer(method, args);
+    }
+    if !RECORDING_ACTIVE.load(Ordering::Relaxed) {
+        return call();
+    }
+
+    let outermost = DEPTH.with(|depth| {
+        depth.set(depth.get() + 1);
+        depth.get() == 1
+    });
+    let _guard = DepthGuard;
+    let result = call();
+    if outermost {
+        let call = match &result {
+            Ok(value) => TracedCall {
+                method: method.to_string(),
+                args,
+                result: Some(serde_json::to_value(value).unwrap_or(serde_json::Value::Null)),
+                error: None,
+            },
+            Err(e) => TracedCall {
+                method: method.to_string(),
+                args,
+                result: None,
+                error: Some(e.to_string()),
+            },
+        };
+        if let Some(trace) = RECORDING.lock().unwrap_or_else(|e| e.into_inner()).as_mut() {
+            trace.calls.push(call);
+        }
+    }
+    result
+}
+
+/// Public traced methods, each calling the `_untraced` implementation
+macro_rules! traced_methods {
+    ($(
+        $(#[$doc:meta])*
+        fn $name:ident(&$self:ident $(, $arg:ident: $ty:ty)*) -> $ret:ty = $untraced:ident;
+    )*) => {
+        impl Guestfs {
+            $(
+                $(#[$doc])*
+                pub fn $name(&$self $(, $arg: $ty)*) -> Result<$ret> {
+                    let replay = $self.replay.clone();
+                    traced(replay.as_deref(), stringify!($name), vec![$($arg.to_string()),*], || {
+                        $self.$untraced($($arg),*)
+                    })
+                }
+            )*
+        }
+    };
+}
+
+macro_rules! traced_mut_methods {
+    ($(
+        $(#[$doc:meta])*
+        fn $name:ident(&mut $self:ident $(, $arg:ident: $ty:ty)*) -> $ret:ty = $untraced:ident;
+    )*) => {
+        impl Guestfs {
+            $(
+                $(#[$doc])*
+                pub fn $name(&mut $self $(, $arg: $ty)*) -> Result<$ret> {
+                    let replay = $self.replay.clone();
+                    traced(replay.as_deref(), stringify!($name), vec![$($arg.to_string()),*], || {
+                        $self.$untraced($($arg),*)
+                    })
+                }
+            )*
+        }
+    };
+}
+
+traced_methods! {
+    /// List block devices
+    fn list_devices(&self) -> Vec<String> = list_devices_untraced;
+    /// List partitions
+    fn list_partitions(&self) -> Vec<String> = list_partitions_untraced;
+    /// Get the size of a device in bytes
+    fn blockdev_getsize64(&self, device: &str) -> i64 = blockdev_getsize64_untraced;
+    /// Get the partition table type (gpt, msdos)
+    fn part_get_parttype(&self, device: &str) -> String = part_get_parttype_untraced;
+    /// Get partition list
+    fn part_list(&self, device: &str) -> Vec<PartInfo> = part_list_untraced;
+    /// Get the partition number of a partition device
+    fn part_to_partnum(&self, partition: &str) -> i32 = part_to_partnum_untraced;
+}
+
+traced_mut_methods! {
+    /// Check if a block device is read-only
+    fn blockdev_getro(&mut self, device: &str) -> bool = blockdev_getro_untraced;
+    /// Get the sector size of a block device
+    fn blockdev_getss(&mut self, device: &str) -> i32 = blockdev_getss_untraced;
+    /// List filesystems and their types
+    fn list_filesystems(&mut self) -> HashMap<String, String> = list_filesystems_untraced;
+    /// Get the filesystem label
+    fn vfs_label(&mut self, device: &str) -> String = vfs_label_untraced;
+    /// Get the filesystem UUID
+    fn vfs_uuid(&mut self, device: &str) -> String = vfs_uuid_untraced;
+    /// Read the LUKS header of a device, None if it is not a LUKS volume
+    fn luks_detect(&mut self, device: &str) -> Option<LuksInfo> = luks_detect_untraced;
+    /// Mount a filesystem
+    fn mount(&mut self, mountable: &str, mountpoint: &str) -> () = mount_untraced;
+    /// Mount a filesystem read-only
+    fn mount_ro(&mut self, mountable: &str, mountpoint: &str) -> () = mount_ro_untraced;
+    /// Unmount a filesystem
+    fn umount(&mut self, pathordevice: &str) -> () = umount_untraced;
+    /// Get filesystem statistics
+    fn statvfs(&mut self, path: &str) -> HashMap<String, i64> = statvfs_untraced;
+    /// List directory contents
+    fn ls(&mut self, directory: &str) -> Vec<String> = ls_untraced;
+    /// List installed Debian packages
+    fn dpkg_list(&mut self) -> Vec<String> = dpkg_list_untraced;
+    /// List installed RPM packages
+    fn rpm_list(&mut self) -> Vec<String> = rpm_list_untraced;
+    /// Inspect the disk for operating systems and return their roots
+    fn inspect_os(&mut self) -> Vec<String> = inspect_os_untraced;
+    /// Get the OS type (linux, windows, ...)
+    fn inspect_get_type(&mut self, root: &str) -> String = inspect_get_type_untraced;
+    /// Get the distribution (fedora, ubuntu, windows, ...)
+    fn inspect_get_distro(&mut self, root: &str) -> String = inspect_get_distro_untraced;
+    /// Get the product name
+    fn inspect_get_product_name(&mut self, root: &str)
+        -> String = inspect_get_product_name_untraced;
+    /// Get the product variant (Server, Workstation, ...)
+    fn inspect_get_product_variant(&mut self, root: &str)
+        -> String = inspect_get_product_variant_untraced;
+    /// Get the architecture
+    fn inspect_get_arch(&mut self, root: &str) -> String = inspect_get_arch_untraced;
+    /// Get the major version
+    fn inspect_get_major_version(&mut self, root: &str) -> i32 = inspect_get_major_version_untraced;
+    /// Get the minor version
+    fn inspect_get_minor_version(&mut self, root: &str) -> i32 = inspect_get_minor_version_untraced;
+    /// Get the hostname
+    fn inspect_get_hostname(&mut self, root: &str) -> String = inspect_get_hostname_untraced;
+    /// Get the package format (rpm, deb, ...)
+    fn inspect_get_package_format(&mut self, root: &str)
+        -> String = inspect_get_package_format_untraced;
+    /// Get the package manager (dnf, apt, ...)
+    fn inspect_get_package_management(&mut self, root: &str)
+        -> String = inspect_get_package_management_untraced;
+    /// Get the format of the install (installed, installer, ...)
+    fn inspect_get_format(&mut self, root: &str) -> String = inspect_get_format_untraced;
+    /// Get the init system
+    fn inspect_get_init_system(&mut self, root: &str) -> String = inspect_get_init_system_untraced;
+    /// Get the mountpoints of the OS
+    fn inspect_get_mountpoints(&mut self, root: &str)
+        -> BTreeMap<String, String> = inspect_get_mountpoints_untraced;
+    /// Check if the OS is a live CD
+    fn inspect_is_live(&mut self, root: &str) -> bool = inspect_is_live_untraced;
+    /// Check if the OS is part of a multipart install
+    fn inspect_is_multipart(&mut self, root: &str) -> bool = inspect_is_multipart_untraced;
+    /// Check if the OS is a network installer
+    fn inspect_is_netinst(&mut self, root: &str) -> bool = inspect_is_netinst_untraced;
+    /// Inspect network interfaces
+    fn inspect_network(&mut self, root: &str) -> Vec<NetworkInterface> = inspect_network_untraced;
+    /// Inspect DNS servers
+    fn inspect_dns(&mut self, root: &str) -> Vec<String> = inspect_dns_untraced;
+    /// Inspect user accounts
+    fn inspect_users(&mut self, root: &str) -> Vec<UserAccount> = inspect_users_untraced;
+    /// Inspect the SSH server configuration
+    fn inspect_ssh_config(&mut self, root: &str)
+        -> HashMap<String, String> = inspect_ssh_config_untraced;
+    /// Inspect the SELinux mode
+    fn inspect_selinux(&mut self, root: &str) -> String = inspect_selinux_untraced;
+    /// Inspect enabled systemd services
+    fn inspect_systemd_services(&mut self, root: &str)
+        -> Vec<SystemService> = inspect_systemd_services_untraced;
+    /// Inspect the timezone
+    fn inspect_timezone(&mut self, root: &str) -> String = inspect_timezone_untraced;
+    /// Inspect the locale
+    fn inspect_locale(&mut self, root: &str) -> String = inspect_locale_untraced;
+    /// Inspect LVM volume groups and logical volumes
+    fn inspect_lvm(&mut self, root: &str) -> LVMInfo = inspect_lvm_untraced;
+    /// Check if cloud-init is installed
+    fn inspect_cloud_init(&mut self, root: &str) -> bool = inspect_cloud_init_untraced;
+    /// Inspect language runtimes
+    fn inspect_runtimes(&mut self, root: &str)
+        -> HashMap<String, String> = inspect_runtimes_untraced;
+    /// Inspect container runtimes
+    fn inspect_container_runtimes(&mut self, root: &str)
+        -> Vec<String> = inspect_container_runtimes_untraced;
+    /// Inspect cron jobs
+    fn inspect_cron(&mut self, root: &str) -> Vec<String> = inspect_cron_untraced;
+    /// Inspect systemd timers
+    fn inspect_systemd_timers(&mut self, root: &str)
+        -> Vec<String> = inspect_systemd_timers_untraced;
+    /// Inspect SSL certificates
+    fn inspect_certificates(&mut self, root: &str)
+        -> Vec<Certificate> = inspect_certificates_untraced;
+    /// Inspect kernel parameters
+    fn inspect_kernel_params(&mut self, root: &str)
+        -> HashMap<String, String> = inspect_kernel_params_untraced;
+    /// Inspect virtualization guest tools
+    fn inspect_vm_tools(&mut self, root: &str) -> Vec<String> = inspect_vm_tools_untraced;
+    /// Inspect swap devices
+    fn inspect_swap(&mut self, root: &str) -> Vec<String> = inspect_swap_untraced;
+    /// Inspect fstab entries (device, mountpoint, filesystem)
+    fn inspect_fstab(&mut self, root: &str)
+        -> Vec<(String, String, String)> = inspect_fstab_untraced;
+    /// Inspect Windows system information
+    fn inspect_windows_system(&mut self, root: &str)
+        -> WindowsSystemInfo = inspect_windows_system_untraced;
+    /// Inspect installed Windows software
+    fn inspect_windows_software(&mut self, root: &str)
+        -> Vec<WindowsApplication> = inspect_windows_software_untraced;
+    /// Inspect Windows services
+    fn inspect_windows_services(&mut self, root: &str)
+        -> Vec<WindowsService> = inspect_windows_services_untraced;
+    /// Inspect Windows network adapters
+    fn inspect_windows_network(&mut self, root: &str)
+        -> Vec<WindowsNetworkAdapter> = inspect_windows_network_untraced;
+    /// Inspect installed Windows updates
+    fn inspect_windows_updates(&mut self, root: &str)
+        -> Vec<WindowsUpdate> = inspect_windows_updates_untraced;
+    /// Inspect Windows scheduled tasks
+    fn inspect_windows_scheduled_tasks(&mut self, root: &str)
+        -> Vec<WindowsScheduledTask> = inspect_windows_scheduled_tasks_untraced;
+    /// Inspect Windows event logs
+    fn inspect_windows_events(&mut self, root: &str, log_name: &str, limit: usize)
+        -> Vec<WindowsEventLogEntry> = inspect_windows_events_untraced;
+}
+
+impl Guestfs {
+    /// Whether the handle answers calls from a session trace
+    pub fn is_replaying(&self) -> bool {
+        self.replay.is_some()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn trace(calls: Vec<TracedCall>) -> Replay {
+        Replay::new(SessionTrace {
+            calls,
+            ..SessionTrace::new()
+        })
+    }
+
+    fn call(method: &str, args: &[&str], result: Option<serde_json::Value>) -> TracedCall {
+        TracedCall {
+            method: method.to_string(),
+            args: args.iter().map(|arg| arg.to_string()).collect(),
+            error: result
+                .is_none()
+                .then(|| "Not found: /etc/os-release".to_string()),
+            result,
+        }
+    }
+
+    #[test]
+    fn test_replay_answers() {
+        let replay = trace(vec![
+            call("inspect_os", &[], Some(serde_json::json!(["/dev/sda2"]))),
+            call(
+                "inspect_get_major_version",
+                &["/dev/sda2"],
+                Some(serde_json::json!(9)),
+            ),
+            call(
+                "inspect_get_major_version",
+                &["/dev/sda2"],
+                Some(serde_json::json!(10)),
+            ),
+            call("inspect_get_distro", &["/dev/sda2"], None),
+        ]);
+
+        let roots: Vec<String> = replay.answer("inspect_os", vec![]).unwrap();
+        assert_eq!(roots, vec!["/dev/sda2"]);
+        let args = || vec!["/dev/sda2".to_string()];
+        for expected in [9, 10, 10] {
+            let major: i32 = replay.answer("inspect_get_major_version", args()).unwrap();
+            assert_eq!(major, expected);
+        }
+
+        let error = replay
+            .answer::<String>("inspect_get_distro", args())
+            .unwrap_err();
+        assert_eq!(error.to_string(), "Not found: /etc/os-release");
+        assert!(matches!(
+            replay.answer::<String>("inspect_get_arch", args()),
+            Err(Error::NotFound(_))
+        ));
+        assert!(matches!(
+            replay.answer::<i32>("inspect_os", vec![]),
+            Err(Error::InvalidFormat(_))
+        ));
+    }
+
+    #[test]
+    fn test_record_outermost_calls() {
+        start_recording();
+        let result: Result<i32> = traced(None, "outer", vec!["a".to_string()], || {
+            traced(None, "inner", vec![], || Ok(1))?;
+            Err(Error::NotFound("x".to_string()))
+        });
+        assert!(result.is_err());
+        let trace = finish_recording().unwrap();
+
+        let outer: Vec<&TracedCall> = trace.calls.iter().filter(|c| c.method == "outer").collect();
+        assert_eq!(outer.len(), 1);
+        assert_eq!(outer[0].error.as_deref(), Some("Not found: x"));
+        assert!(trace.calls.iter().all(|c| c.method != "inner"));
+
+        let json = trace.to_json().unwrap();
+        assert_eq!(SessionTrace::from_json(&json).unwrap(), trace);
+    }
+}
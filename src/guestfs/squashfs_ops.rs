@@ -5,8 +5,38 @@
 
 use crate::core::{Error, Result};
 use crate::guestfs::Guestfs;
+use std::fs::File;
+use std::io::Read;
+use std::path::Path;
 use std::process::Command;
 
+/// SquashFS magic "hsqs", at the start of the image
+const SQUASHFS_MAGIC: &[u8; 4] = b"hsqs";
+/// EROFS magic 0xE0F5E1E2 (little-endian), at offset 1024
+const EROFS_MAGIC: &[u8; 4] = &[0xe2, 0xe1, 0xf5, 0xe0];
+const EROFS_SUPERBLOCK_OFFSET: usize = 1024;
+
+/// Filesystem type of a read-only root image, for mounting it
+///
+/// `squashfs` or `erofs`, or `None` for other images, which `mount` probes
+/// itself.
+pub fn image_vfs_type(image: &Path) -> Result<Option<&'static str>> {
+    let mut header = Vec::with_capacity(EROFS_SUPERBLOCK_OFFSET + 4);
+    File::open(image)
+        .map_err(Error::Io)?
+        .take((EROFS_SUPERBLOCK_OFFSET + 4) as u64)
+        .read_to_end(&mut header)
+        .map_err(Error::Io)?;
+
+    if header.starts_with(SQUASHFS_MAGIC) {
+        Ok(Some("squashfs"))
+    } else if header.get(EROFS_SUPERBLOCK_OFFSET..) == Some(&EROFS_MAGIC[..]) {
+        Ok(Some("erofs"))
+    } else {
+        Ok(None)
+    }
+}
+
 impl Guestfs {
     /// Create SquashFS filesystem
     ///
@@ -124,4 +154,22 @@ mod tests {
         let mut g = Guestfs::new().unwrap();
         // API structure tests
     }
+
+    #[test]
+    fn test_image_vfs_type() {
+        let dir = tempfile::tempdir().unwrap();
+        let image = dir.path().join("image");
+
+        std::fs::write(&image, b"hsqs\x10\0\0\0").unwrap();
+        assert_eq!(image_vfs_type(&image).unwrap(), Some("squashfs"));
+
+        let mut erofs = vec![0u8; 2048];
+        erofs[1024..1028].copy_from_slice(&0xe0f5_e1e2u32.to_le_bytes());
+        std::fs::write(&image, &erofs).unwrap();
+        assert_eq!(image_vfs_type(&image).unwrap(), Some("erofs"));
+
+        std::fs::write(&image, [0u8; 512]).unwrap();
+        assert_eq!(image_vfs_type(&image).unwrap(), None);
+        assert!(image_vfs_type(&dir.path().join("missing")).is_err());
+    }
 }
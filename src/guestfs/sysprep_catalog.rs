@@ -0,0 +1,705 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Catalog of named sysprep operations
+//!
+//! Each operation removes, truncates or creates a fixed set of guest paths,
+//! named like the operations of virt-sysprep. Patterns may use `*`, `?` and
+//! `[...]` within a path component. Paths are resolved without following
+//! symlinks, so a link is removed rather than what it points to.
+
+use crate::core::{Error, Result};
+use crate::guestfs::Guestfs;
+use serde::Serialize;
+use std::path::{Path, PathBuf};
+
+/// A sysprep operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct SysprepOperation {
+    pub name: &'static str,
+    pub description: &'static str,
+    /// Selected when no operations are named
+    pub default: bool,
+    /// Files and directories removed
+    #[serde(skip)]
+    remove: &'static [&'static str],
+    /// Files emptied but kept
+    #[serde(skip)]
+    truncate: &'static [&'static str],
+    /// Empty files created
+    #[serde(skip)]
+    create: &'static [&'static str],
+}
+
+const fn remove(
+    name: &'static str,
+    default: bool,
+    description: &'static str,
+    remove: &'static [&'static str],
+) -> SysprepOperation {
+    SysprepOperation {
+        name,
+        description,
+        default,
+        remove,
+        truncate: &[],
+        create: &[],
+    }
+}
+
+static OPERATIONS: &[SysprepOperation] = &[
+    remove(
+        "abrt-data",
+        true,
+        "Remove the crash data generated by ABRT",
+        &["/var/spool/abrt/*"],
+    ),
+    remove(
+        "backup-files",
+        true,
+        "Remove editor and package backup files from /etc",
+        &[
+            "/etc/*~",
+            "/etc/*/*~",
+            "/etc/*.bak",
+            "/etc/*/*.bak",
+            "/etc/*.rpmsave",
+            "/etc/*/*.rpmsave",
+            "/etc/*.dpkg-old",
+            "/etc/*/*.dpkg-old",
+        ],
+    ),
+    remove(
+        "bash-history",
+        true,
+        "Remove the bash history of root and users",
+        &["/root/.bash_history", "/home/*/.bash_history"],
+    ),
+    remove(
+        "blkid-tab",
+        true,
+        "Remove the blkid cache",
+        &[
+            "/var/run/blkid.tab",
+            "/var/run/blkid.tab.old",
+            "/etc/blkid/blkid.tab",
+            "/etc/blkid/blkid.tab.old",
+            "/etc/blkid.tab",
+            "/etc/blkid.tab.old",
+        ],
+    ),
+    remove(
+        "ca-certificates",
+        false,
+        "Remove local CA certificates and TLS private keys",
+        &[
+            "/etc/pki/CA/certs/*.crt",
+            "/etc/pki/CA/crl/*.crt",
+            "/etc/pki/CA/newcerts/*.crt",
+            "/etc/pki/CA/private/*.key",
+            "/etc/pki/tls/private/*.key",
+            "/etc/pki/tls/certs/*.crt",
+        ],
+    ),
+    remove(
+        "cloud-init-state",
+        true,
+        "Remove cloud-init instance state, so it runs again on next boot",
+        &[
+            "/var/lib/cloud/instance",
+            "/var/lib/cloud/instances/*",
+            "/var/lib/cloud/data/*",
+            "/var/lib/cloud/sem/*",
+        ],
+    ),
+    remove(
+        "crash-data",
+        true,
+        "Remove kernel crash dumps",
+        &["/var/crash/*", "/var/log/dump/*"],
+    ),
+    remove(
+        "cron-spool",
+        true,
+        "Remove user crontabs and pending at jobs",
+        &["/var/spool/cron/*", "/var/spool/at/*"],
+    ),
+    remove(
+        "dhcp-leases",
+        true,
+        "Remove DHCP client leases",
+        &[
+            "/var/lib/dhclient/*",
+            "/var/lib/dhcp/*",
+            "/var/lib/NetworkManager/*.lease",
+            "/var/lib/NetworkManager/dhclient-*",
+            "/var/lib/NetworkManager/internal-*",
+            "/var/lib/systemd/netif/leases/*",
+        ],
+    ),
+    remove(
+        "dhcp-server-state",
+        true,
+        "Remove DHCP server leases",
+        &["/var/lib/dhcpd/*", "/var/lib/dhcp/dhcpd.leases*"],
+    ),
+    remove(
+        "dovecot-data",
+        true,
+        "Remove Dovecot mail server data",
+        &["/var/lib/dovecot/*"],
+    ),
+    remove(
+        "firewall-rules",
+        false,
+        "Remove custom firewall rules",
+        &[
+            "/etc/sysconfig/iptables",
+            "/etc/sysconfig/ip6tables",
+            "/etc/firewalld/services/*",
+            "/etc/firewalld/zones/*",
+        ],
+    ),
+    SysprepOperation {
+        name: "flag-reconfiguration",
+        description: "Flag the system for reconfiguration on next boot",
+        default: false,
+        remove: &[],
+        truncate: &[],
+        create: &["/.unconfigured"],
+    },
+    remove(
+        "ipa-client",
+        true,
+        "Remove the IPA client enrollment",
+        &[
+            "/etc/ipa/ca.crt",
+            "/etc/ipa/default.conf",
+            "/var/lib/ipa-client/sysrestore/*",
+            "/var/lib/ipa-client/pki/*",
+        ],
+    ),
+    remove(
+        "kerberos-data",
+        false,
+        "Remove the Kerberos KDC database",
+        &[
+            "/var/kerberos/krb5kdc/principal*",
+            "/var/kerberos/krb5kdc/.k5.*",
+        ],
+    ),
+    remove(
+        "kerberos-hostkeyfile",
+        true,
+        "Remove the Kerberos host keytab",
+        &["/etc/krb5.keytab", "/etc/krb5.keytab.*"],
+    ),
+    remove(
+        "logfiles",
+        true,
+        "Remove log files and the systemd journal",
+        &[
+            "/var/log/*.log",
+            "/var/log/*.log.*",
+            "/var/log/*/*.log",
+            "/var/log/*/*.log.*",
+            "/var/log/messages*",
+            "/var/log/secure*",
+            "/var/log/syslog*",
+            "/var/log/maillog*",
+            "/var/log/cron*",
+            "/var/log/dmesg*",
+            "/var/log/btmp*",
+            "/var/log/wtmp*",
+            "/var/log/lastlog*",
+            "/var/log/audit/*",
+            "/var/log/journal/*",
+            "/root/anaconda-ks.cfg",
+            "/root/original-ks.cfg",
+        ],
+    ),
+    remove(
+        "lvm-system-devices",
+        true,
+        "Remove the LVM devices file, which names the host's disks",
+        &["/etc/lvm/devices/system.devices"],
+    ),
+    SysprepOperation {
+        name: "machine-id",
+        description: "Clear the machine ID, so a new one is made on next boot",
+        default: true,
+        remove: &["/var/lib/dbus/machine-id"],
+        truncate: &["/etc/machine-id"],
+        create: &[],
+    },
+    remove(
+        "mail-spool",
+        true,
+        "Remove local mail",
+        &["/var/spool/mail/*", "/var/mail/*"],
+    ),
+    remove(
+        "net-nmconn",
+        true,
+        "Remove NetworkManager connection profiles",
+        &[
+            "/etc/NetworkManager/system-connections/*",
+            "/var/lib/NetworkManager/seen-bssids",
+            "/var/lib/NetworkManager/timestamps",
+        ],
+    ),
+    remove(
+        "package-manager-cache",
+        true,
+        "Remove package manager caches",
+        &[
+            "/var/cache/apt/archives/*.deb",
+            "/var/cache/apt/*.bin",
+            "/var/cache/dnf/*",
+            "/var/cache/yum/*",
+            "/var/cache/zypp/*",
+            "/var/cache/apk/*",
+        ],
+    ),
+    remove(
+        "package-manager-db",
+        true,
+        "Remove RPM database environment files, rebuilt on next use",
+        &["/var/lib/rpm/__db.*"],
+    ),
+    remove(
+        "pacct-log",
+        true,
+        "Remove process accounting logs",
+        &["/var/account/pacct*", "/var/log/account/pacct*"],
+    ),
+    remove(
+        "passwd-backups",
+        true,
+        "Remove backups of the account files",
+        &[
+            "/etc/passwd-",
+            "/etc/shadow-",
+            "/etc/group-",
+            "/etc/gshadow-",
+        ],
+    ),
+    remove(
+        "puppet-data-log",
+        true,
+        "Remove Puppet logs and client data",
+        &["/var/log/puppet/*", "/var/lib/puppet/*/*"],
+    ),
+    remove(
+        "random-seed",
+        true,
+        "Remove the saved random seed",
+        &["/var/lib/systemd/random-seed", "/var/lib/random-seed"],
+    ),
+    remove(
+        "rh-subscription-manager",
+        true,
+        "Remove the RHSM registration",
+        &[
+            "/var/lib/rhsm/*",
+            "/etc/pki/consumer/*",
+            "/etc/pki/entitlement/*.pem",
+        ],
+    ),
+    remove(
+        "rhn-systemid",
+        true,
+        "Remove the RHN system ID",
+        &[
+            "/etc/sysconfig/rhn/systemid",
+            "/etc/sysconfig/rhn/osad-auth.conf",
+        ],
+    ),
+    remove(
+        "samba-db-log",
+        true,
+        "Remove Samba logs and state",
+        &["/var/log/samba/*", "/var/lib/samba/*/*"],
+    ),
+    remove(
+        "smolt-uuid",
+        true,
+        "Remove the Smolt hardware UUID",
+        &[
+            "/etc/sysconfig/hw-uuid",
+            "/etc/smolt/uuid",
+            "/etc/smolt/hw-uuid",
+        ],
+    ),
+    remove(
+        "ssh-hostkeys",
+        true,
+        "Remove SSH host keys, regenerated on next boot",
+        &["/etc/ssh/*_host_*"],
+    ),
+    remove(
+        "ssh-userdir",
+        false,
+        "Remove the .ssh directories of root and users",
+        &["/root/.ssh", "/home/*/.ssh"],
+    ),
+    remove(
+        "sssd-db-log",
+        true,
+        "Remove SSSD logs and caches",
+        &["/var/log/sssd/*", "/var/lib/sss/db/*"],
+    ),
+    remove(
+        "tmp-files",
+        true,
+        "Remove temporary files",
+        &["/tmp/*", "/var/tmp/*"],
+    ),
+    remove(
+        "udev-persistent-net",
+        true,
+        "Remove udev persistent network rules, which pin MAC addresses",
+        &["/etc/udev/rules.d/70-persistent-net.rules"],
+    ),
+    SysprepOperation {
+        name: "utmp",
+        description: "Clear the record of logged-in users",
+        default: true,
+        remove: &[],
+        truncate: &["/var/run/utmp", "/run/utmp"],
+        create: &[],
+    },
+    remove(
+        "yum-uuid",
+        true,
+        "Remove the yum UUID",
+        &["/var/lib/yum/uuid"],
+    ),
+    remove(
+        "zsh-history",
+        true,
+        "Remove the zsh history of root and users",
+        &["/root/.zsh_history", "/home/*/.zsh_history"],
+    ),
+];
+
+/// All sysprep operations, by name
+pub fn sysprep_operations() -> &'static [SysprepOperation] {
+    OPERATIONS
+}
+
+/// Look up an operation by name
+pub fn sysprep_operation(name: &str) -> Option<&'static SysprepOperation> {
+    OPERATIONS.iter().find(|op| op.name == name)
+}
+
+/// Operations selected by `ops` less those in `skip`, in catalog order
+///
+/// No `ops` selects the default operations. `ops` may name `defaults` and
+/// `all` besides operations.
+pub fn select_sysprep_operations(
+    ops: &[String],
+    skip: &[String],
+) -> Result<Vec<&'static SysprepOperation>> {
+    let lookup = |name: &str| {
+        sysprep_operation(name).ok_or_else(|| {
+            let names: Vec<&str> = OPERATIONS.iter().map(|op| op.name).collect();
+            Error::InputValidation(format!(
+                "Unknown sysprep operation '{}' (operations: {})",
+                name,
+                names.join(", ")
+            ))
+        })
+    };
+
+    let mut selected: Vec<&str> = Vec::new();
+    if ops.is_empty() {
+        selected.push("defaults");
+    }
+    selected.extend(ops.iter().map(String::as_str));
+    let mut names: Vec<&str> = Vec::new();
+    for name in selected {
+        match name {
+            "defaults" => names.extend(OPERATIONS.iter().filter(|op| op.default).map(|op| op.name)),
+            "all" => names.extend(OPERATIONS.iter().map(|op| op.name)),
+            name => names.push(lookup(name)?.name),
+        }
+    }
+    for name in skip {
+        lookup(name)?;
+    }
+
+    Ok(OPERATIONS
+        .iter()
+        .filter(|op| names.contains(&op.name) && !skip.iter().any(|name| name == op.name))
+        .collect())
+}
+
+/// What an operation changes, or changed
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct SysprepResult {
+    pub operation: String,
+    pub removed: Vec<String>,
+    pub truncated: Vec<String>,
+    pub created: Vec<String>,
+}
+
+impl SysprepResult {
+    pub fn is_empty(&self) -> bool {
+        self.removed.is_empty() && self.truncated.is_empty() && self.created.is_empty()
+    }
+}
+
+impl SysprepOperation {
+    /// What the operation would change in the guest mounted at `root`
+    fn plan(&self, root: &Path) -> SysprepResult {
+        let expand = |patterns: &[&str]| {
+            let mut paths: Vec<String> = patterns
+                .iter()
+                .flat_map(|pattern| expand_in(root, pattern))
+                .collect();
+            paths.sort();
+            paths.dedup();
+            paths
+        };
+        SysprepResult {
+            operation: self.name.to_string(),
+            removed: expand(self.remove),
+            truncated: expand(self.truncate),
+            created: self
+                .create
+                .iter()
+                .filter(|path| host_path(root, path).is_none())
+                .map(|path| path.to_string())
+                .collect(),
+        }
+    }
+
+    /// Apply the operation to the guest mounted at `root`
+    fn apply(&self, root: &Path) -> Result<SysprepResult> {
+        let result = self.plan(root);
+        for path in &result.removed {
+            let Some(host) = host_path(root, path) else {
+                continue;
+            };
+            let removed = if host.symlink_metadata().map_err(Error::Io)?.is_dir() {
+                std::fs::remove_dir_all(&host)
+            } else {
+                std::fs::remove_file(&host)
+            };
+            removed.map_err(|e| {
+                Error::Io(std::io::Error::new(e.kind(), format!("{}: {}", path, e)))
+            })?;
+        }
+        for path in &result.truncated {
+            if let Some(host) = host_path(root, path).filter(|host| host.is_file()) {
+                std::fs::OpenOptions::new()
+                    .write(true)
+                    .truncate(true)
+                    .open(&host)
+                    .map_err(Error::Io)?;
+            }
+        }
+        for path in &result.created {
+            let (parent, name) = split_path(path);
+            if let Some(dir) = host_dir(root, parent) {
+                std::fs::write(dir.join(name), b"").map_err(Error::Io)?;
+            }
+        }
+        Ok(result)
+    }
+}
+
+impl Guestfs {
+    /// Run sysprep operations on the mounted guest, or with `dry_run` only
+    /// report what they would change
+    pub fn sysprep_run(
+        &mut self,
+        operations: &[&SysprepOperation],
+        dry_run: bool,
+    ) -> Result<Vec<SysprepResult>> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: sysprep_run dry_run={}", dry_run);
+        }
+
+        let root = PathBuf::from(self.find_root_mountpoint()?);
+        operations
+            .iter()
+            .map(|op| {
+                if self.verbose {
+                    eprintln!("guestfs: sysprep operation {}", op.name);
+                }
+                if dry_run {
+                    Ok(op.plan(&root))
+                } else {
+                    op.apply(&root)
+                }
+            })
+            .collect()
+    }
+}
+
+fn split_path(path: &str) -> (&str, &str) {
+    match path.rsplit_once('/') {
+        Some(("", name)) => ("/", name),
+        Some((parent, name)) => (parent, name),
+        None => ("/", path),
+    }
+}
+
+/// Host path of the guest path `path` under `root`, if it exists
+///
+/// The parent directory is resolved inside `root` and the last component
+/// is not followed, so a symlink names itself.
+fn host_path(root: &Path, path: &str) -> Option<PathBuf> {
+    let (parent, name) = split_path(path);
+    if name.is_empty() || name == "." || name == ".." {
+        return None;
+    }
+    let host = host_dir(root, parent)?.join(name);
+    host.symlink_metadata().ok().map(|_| host)
+}
+
+/// Host path of the guest directory `dir` under `root`, if it is one
+fn host_dir(root: &Path, dir: &str) -> Option<PathBuf> {
+    let root = root.canonicalize().ok()?;
+    let host = root.join(dir.trim_start_matches('/')).canonicalize().ok()?;
+    (host.starts_with(&root) && host.is_dir()).then_some(host)
+}
+
+/// Guest paths under `root` matching `pattern`
+fn expand_in(root: &Path, pattern: &str) -> Vec<String> {
+    let mut paths = vec![String::new()];
+    for component in pattern.split('/').filter(|c| !c.is_empty()) {
+        let mut next = Vec::new();
+        for dir in &paths {
+            if !component.contains(['*', '?', '[']) {
+                next.push(format!("{}/{}", dir, component));
+                continue;
+            }
+            let Ok(matcher) = glob::Pattern::new(component) else {
+                continue;
+            };
+            let Some(Ok(entries)) = host_dir(root, dir).map(std::fs::read_dir) else {
+                continue;
+            };
+            let mut names: Vec<String> = entries
+                .filter_map(|entry| entry.ok())
+                .filter_map(|entry| entry.file_name().into_string().ok())
+                .filter(|name| matcher.matches(name))
+                .collect();
+            names.sort();
+            next.extend(names.into_iter().map(|name| format!("{}/{}", dir, name)));
+        }
+        paths = next;
+    }
+    paths
+        .into_iter()
+        .filter(|path| !path.is_empty() && host_path(root, path).is_some())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tempfile::TempDir;
+
+    fn guest(files: &[&str]) -> TempDir {
+        let root = TempDir::new().unwrap();
+        for file in files {
+            let path = root.path().join(file.trim_start_matches('/'));
+            std::fs::create_dir_all(path.parent().unwrap()).unwrap();
+            std::fs::write(path, b"data").unwrap();
+        }
+        root
+    }
+
+    #[test]
+    fn test_select_operations() {
+        let names =
+            |ops: Vec<&SysprepOperation>| -> Vec<&str> { ops.iter().map(|op| op.name).collect() };
+        let defaults = select_sysprep_operations(&[], &[]).unwrap();
+        assert!(names(defaults.clone()).contains(&"machine-id"));
+        assert!(!names(defaults).contains(&"ssh-userdir"));
+
+        let ops = ["ssh-userdir".to_string(), "logfiles".to_string()];
+        assert_eq!(
+            names(select_sysprep_operations(&ops, &[]).unwrap()),
+            vec!["logfiles", "ssh-userdir"]
+        );
+
+        let skip = ["machine-id".to_string()];
+        let selected = select_sysprep_operations(&["all".to_string()], &skip).unwrap();
+        assert_eq!(selected.len(), sysprep_operations().len() - 1);
+
+        assert!(select_sysprep_operations(&["no-such-op".to_string()], &[]).is_err());
+        assert!(select_sysprep_operations(&[], &["no-such-op".to_string()]).is_err());
+    }
+
+    #[test]
+    fn test_catalog_names_unique() {
+        let mut names: Vec<&str> = sysprep_operations().iter().map(|op| op.name).collect();
+        names.sort();
+        names.dedup();
+        assert_eq!(names.len(), sysprep_operations().len());
+    }
+
+    #[test]
+    fn test_plan_and_apply() {
+        let root = guest(&[
+            "/etc/machine-id",
+            "/etc/ssh/ssh_host_ed25519_key",
+            "/etc/ssh/ssh_host_ed25519_key.pub",
+            "/etc/ssh/sshd_config",
+            "/home/alice/.bash_history",
+            "/home/bob/.profile",
+        ]);
+        std::fs::create_dir_all(root.path().join("tmp")).unwrap();
+        std::os::unix::fs::symlink("../etc/ssh", root.path().join("tmp/ssh")).unwrap();
+
+        let ssh = sysprep_operation("ssh-hostkeys").unwrap();
+        let plan = ssh.plan(root.path());
+        assert_eq!(
+            plan.removed,
+            vec![
+                "/etc/ssh/ssh_host_ed25519_key",
+                "/etc/ssh/ssh_host_ed25519_key.pub"
+            ]
+        );
+        assert!(root.path().join("etc/ssh/ssh_host_ed25519_key").exists());
+
+        ssh.apply(root.path()).unwrap();
+        assert!(!root.path().join("etc/ssh/ssh_host_ed25519_key").exists());
+        assert!(root.path().join("etc/ssh/sshd_config").exists());
+
+        let history = sysprep_operation("bash-history")
+            .unwrap()
+            .apply(root.path())
+            .unwrap();
+        assert_eq!(history.removed, vec!["/home/alice/.bash_history"]);
+
+        let machine_id = sysprep_operation("machine-id")
+            .unwrap()
+            .apply(root.path())
+            .unwrap();
+        assert_eq!(machine_id.truncated, vec!["/etc/machine-id"]);
+        assert_eq!(
+            std::fs::read(root.path().join("etc/machine-id")).unwrap(),
+            b""
+        );
+
+        // The link goes, not the directory it points to
+        let tmp = sysprep_operation("tmp-files")
+            .unwrap()
+            .apply(root.path())
+            .unwrap();
+        assert_eq!(tmp.removed, vec!["/tmp/ssh"]);
+        assert!(root.path().join("etc/ssh/sshd_config").exists());
+
+        let flag = sysprep_operation("flag-reconfiguration").unwrap();
+        assert_eq!(flag.plan(root.path()).created, vec!["/.unconfigured"]);
+        flag.apply(root.path()).unwrap();
+        assert!(root.path().join(".unconfigured").exists());
+        assert!(flag.plan(root.path()).is_empty());
+    }
+}
@@ -4,9 +4,16 @@
 //! This implementation provides forensic analysis functionality.
 
 use crate::core::{Error, Result};
+use crate::disk::Ext4Deleted;
 use crate::guestfs::Guestfs;
+use std::collections::HashMap;
 use std::process::Command;
 
+/// MFT record of the NTFS root directory
+const MFT_ROOT: u64 = 5;
+/// Seconds from 1601, where FILETIMEs start, to the Unix epoch
+const FILETIME_EPOCH: i64 = 11_644_473_600;
+
 impl Guestfs {
     /// Download deleted file using TSK
     ///
@@ -17,22 +24,7 @@ impl Guestfs {
             eprintln!("guestfs: download_inode {} {} {}", device, inode, filename);
         }
 
-        self.setup_nbd_if_needed()?;
-
-        let nbd_partition =
-            if let Some(partition_number) = device.chars().last().and_then(|c| c.to_digit(10)) {
-                let nbd_device = self
-                    .nbd_device
-                    .as_ref()
-                    .ok_or_else(|| Error::InvalidState("NBD device not available".to_string()))?;
-                format!(
-                    "{}p{}",
-                    nbd_device.device_path().display(),
-                    partition_number
-                )
-            } else {
-                return Err(Error::InvalidFormat(format!("Invalid device: {}", device)));
-            };
+        let nbd_partition = self.tsk_partition(device)?;
 
         let output = Command::new("icat")
             .arg(&nbd_partition)
@@ -61,22 +53,7 @@ impl Guestfs {
             eprintln!("guestfs: filesystem_walk {}", device);
         }
 
-        self.setup_nbd_if_needed()?;
-
-        let nbd_partition =
-            if let Some(partition_number) = device.chars().last().and_then(|c| c.to_digit(10)) {
-                let nbd_device = self
-                    .nbd_device
-                    .as_ref()
-                    .ok_or_else(|| Error::InvalidState("NBD device not available".to_string()))?;
-                format!(
-                    "{}p{}",
-                    nbd_device.device_path().display(),
-                    partition_number
-                )
-            } else {
-                return Err(Error::InvalidFormat(format!("Invalid device: {}", device)));
-            };
+        let nbd_partition = self.tsk_partition(device)?;
 
         let output = Command::new("fls")
             .arg("-r")
@@ -146,22 +123,7 @@ impl Guestfs {
             eprintln!("guestfs: tsk_stat {} {}", device, inode);
         }
 
-        self.setup_nbd_if_needed()?;
-
-        let nbd_partition =
-            if let Some(partition_number) = device.chars().last().and_then(|c| c.to_digit(10)) {
-                let nbd_device = self
-                    .nbd_device
-                    .as_ref()
-                    .ok_or_else(|| Error::InvalidState("NBD device not available".to_string()))?;
-                format!(
-                    "{}p{}",
-                    nbd_device.device_path().display(),
-                    partition_number
-                )
-            } else {
-                return Err(Error::InvalidFormat(format!("Invalid device: {}", device)));
-            };
+        let nbd_partition = self.tsk_partition(device)?;
 
         let output = Command::new("istat")
             .arg(&nbd_partition)
@@ -187,6 +149,176 @@ impl Guestfs {
 
         Ok(info)
     }
+
+    /// Bodyfile (TSK 3.x format) of a filesystem, from `fls -m`
+    ///
+    /// Paths are prefixed with `mount_point`. Deleted entries are included
+    /// and have ` (deleted)` appended to their name.
+    pub fn filesystem_bodyfile(
+        &mut self,
+        device: &str,
+        mount_point: &str,
+    ) -> Result<Vec<BodyfileEntry>> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: filesystem_bodyfile {} {}", device, mount_point);
+        }
+
+        let nbd_partition = self.tsk_partition(device)?;
+
+        let output = Command::new("fls")
+            .arg("-r")
+            .arg("-m")
+            .arg(mount_point)
+            .arg(&nbd_partition)
+            .output()
+            .map_err(|e| Error::CommandFailed(format!("Failed to execute fls: {}", e)))?;
+
+        if !output.status.success() {
+            return Err(Error::CommandFailed(format!(
+                "fls failed: {}",
+                String::from_utf8_lossy(&output.stderr)
+            )));
+        }
+
+        Ok(String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .filter_map(BodyfileEntry::parse)
+            .collect())
+    }
+
+    /// Deleted files of the ext2/3/4 or NTFS filesystem on `device`
+    ///
+    /// ext filesystems are read with the pure Rust reader, so `device`
+    /// must not be mounted: deleted inodes whose block map survives in the
+    /// inode table or the journal, and orphans. NTFS ones are the
+    /// unallocated records of `$MFT`, which `icat` extracts.
+    pub fn deleted_files(&mut self, device: &str) -> Result<Vec<DeletedFile>> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: deleted_files {}", device);
+        }
+
+        let fs_type = self.vfs_type(device)?;
+        match fs_type.as_str() {
+            "ext2" | "ext3" | "ext4" => Ok(self
+                .ext4_open(device, false)?
+                .deleted()?
+                .into_iter()
+                .map(|file| DeletedFile {
+                    device: device.to_string(),
+                    inode: u64::from(file.ino),
+                    path: file.path.clone(),
+                    size: file.size,
+                    mtime: file.mtime,
+                    deleted: file.dtime,
+                    source: if file.orphan {
+                        "orphan"
+                    } else if file.sequence.is_some() {
+                        "journal"
+                    } else {
+                        "inode"
+                    },
+                    overwritten: file.blocks_reused > 0,
+                    ext4: Some(file),
+                    resident: None,
+                })
+                .collect()),
+            "ntfs" => {
+                let nbd_partition = self.tsk_partition(device)?;
+                let output = Command::new("icat")
+                    .arg(&nbd_partition)
+                    .arg("0")
+                    .output()
+                    .map_err(|e| Error::CommandFailed(format!("Failed to execute icat: {}", e)))?;
+
+                if !output.status.success() {
+                    return Err(Error::CommandFailed(format!(
+                        "icat failed: {}",
+                        String::from_utf8_lossy(&output.stderr)
+                    )));
+                }
+
+                Ok(deleted_mft_records(output.stdout)
+                    .into_iter()
+                    .map(|(path, record)| DeletedFile {
+                        device: device.to_string(),
+                        inode: record.number,
+                        path: Some(path),
+                        size: record.size,
+                        mtime: record.mtime,
+                        deleted: 0,
+                        source: "mft",
+                        overwritten: false,
+                        ext4: None,
+                        resident: record.resident,
+                    })
+                    .collect())
+            }
+            other => Err(Error::Unsupported(format!(
+                "recovering deleted files from {} ({})",
+                other, device
+            ))),
+        }
+    }
+
+    /// Write what is left of a deleted file to `filename`; returns the
+    /// bytes written
+    pub fn recover_deleted(&mut self, file: &DeletedFile, filename: &str) -> Result<u64> {
+        if self.verbose {
+            eprintln!(
+                "guestfs: recover_deleted {} {} {}",
+                file.device, file.inode, filename
+            );
+        }
+
+        let data = if let Some(deleted) = &file.ext4 {
+            self.ext4_open(&file.device, false)?.recover(deleted)?
+        } else if let Some(data) = &file.resident {
+            data.clone()
+        } else {
+            self.ensure_ready()?;
+            let nbd_partition = self.tsk_partition(&file.device)?;
+            // -r recovers the data runs of unallocated records
+            let output = Command::new("icat")
+                .arg("-r")
+                .arg(&nbd_partition)
+                .arg(file.inode.to_string())
+                .output()
+                .map_err(|e| Error::CommandFailed(format!("Failed to execute icat: {}", e)))?;
+
+            if !output.status.success() {
+                return Err(Error::CommandFailed(format!(
+                    "icat failed: {}",
+                    String::from_utf8_lossy(&output.stderr)
+                )));
+            }
+            output.stdout
+        };
+
+        std::fs::write(filename, &data).map_err(Error::Io)?;
+        Ok(data.len() as u64)
+    }
+
+    /// NBD partition device for a guest partition such as `/dev/sda2`
+    fn tsk_partition(&mut self, device: &str) -> Result<String> {
+        self.setup_nbd_if_needed()?;
+
+        let Some(partition_number) = device.chars().last().and_then(|c| c.to_digit(10)) else {
+            return Err(Error::InvalidFormat(format!("Invalid device: {}", device)));
+        };
+        let nbd_device = self
+            .nbd_device
+            .as_ref()
+            .ok_or_else(|| Error::InvalidState("NBD device not available".to_string()))?;
+        Ok(format!(
+            "{}p{}",
+            nbd_device.device_path().display(),
+            partition_number
+        ))
+    }
 }
 
 /// TSK directory entry
@@ -199,6 +331,260 @@ pub struct TskDirent {
     pub size: i64,
 }
 
+/// A deleted file found by [`Guestfs::deleted_files`]
+#[derive(Debug, Clone)]
+pub struct DeletedFile {
+    pub device: String,
+    /// Inode, or MFT record number
+    pub inode: u64,
+    /// Path from the filesystem root, if a name survives
+    pub path: Option<String>,
+    pub size: u64,
+    pub mtime: i64,
+    /// When it was deleted, 0 where the filesystem doesn't record it
+    pub deleted: i64,
+    /// Where it was found: `inode`, `journal`, `orphan` or `mft`
+    pub source: &'static str,
+    /// Some of its blocks have been reused by other files since
+    pub overwritten: bool,
+    ext4: Option<Ext4Deleted>,
+    /// Data NTFS kept in the record itself
+    resident: Option<Vec<u8>>,
+}
+
+/// The parts of an NTFS `$MFT` record recovery needs
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct MftRecord {
+    number: u64,
+    in_use: bool,
+    directory: bool,
+    parent: u64,
+    name: Option<String>,
+    size: u64,
+    mtime: i64,
+    resident: Option<Vec<u8>>,
+}
+
+fn le_u16(buf: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        buf.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn le_u32(buf: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        buf.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn le_u64(buf: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        buf.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+fn filetime(value: u64) -> i64 {
+    (value / 10_000_000) as i64 - FILETIME_EPOCH
+}
+
+impl MftRecord {
+    /// Parse record `number`, undoing its update sequence fixups; `None`
+    /// for free, corrupt and extension records
+    fn parse(number: u64, record: &mut [u8]) -> Option<Self> {
+        if record.get(..4)? != b"FILE" {
+            return None;
+        }
+        let usa = usize::from(le_u16(record, 0x4)?);
+        let count = usize::from(le_u16(record, 0x6)?);
+        let check = le_u16(record, usa)?;
+        for i in 1..count {
+            let end = i * 512 - 2;
+            if le_u16(record, end)? != check {
+                return None;
+            }
+            let fixup = le_u16(record, usa + 2 * i)?;
+            record[end..end + 2].copy_from_slice(&fixup.to_le_bytes());
+        }
+        if le_u64(record, 0x20)? != 0 {
+            return None;
+        }
+
+        let flags = le_u16(record, 0x16)?;
+        let mut parsed = Self {
+            number,
+            in_use: flags & 0x1 != 0,
+            directory: flags & 0x2 != 0,
+            parent: 0,
+            name: None,
+            size: 0,
+            mtime: 0,
+            resident: None,
+        };
+        let mut offset = usize::from(le_u16(record, 0x14)?);
+        let mut dos_name = true;
+        while let Some(kind) = le_u32(record, offset).filter(|&kind| kind != 0xffff_ffff) {
+            let len = le_u32(record, offset + 4)? as usize;
+            if len == 0 {
+                break;
+            }
+            let attr = record.get(offset..offset + len)?;
+            let non_resident = attr[8] != 0;
+            let unnamed = attr[9] == 0;
+            let content = if non_resident {
+                &[][..]
+            } else {
+                let at = usize::from(le_u16(attr, 0x14)?);
+                attr.get(at..at + le_u32(attr, 0x10)? as usize)?
+            };
+            match kind {
+                // $STANDARD_INFORMATION
+                0x10 => parsed.mtime = le_u64(content, 0x8).map_or(0, filetime),
+                // $FILE_NAME; DOS 8.3 names (namespace 2) only as a fallback
+                0x30 => {
+                    let name_len = usize::from(*content.get(0x40)?);
+                    let namespace = *content.get(0x41)?;
+                    if parsed.name.is_none() || dos_name && namespace != 2 {
+                        let units: Vec<u16> = content
+                            .get(0x42..0x42 + 2 * name_len)?
+                            .chunks_exact(2)
+                            .map(|unit| u16::from_le_bytes([unit[0], unit[1]]))
+                            .collect();
+                        parsed.name = Some(String::from_utf16_lossy(&units));
+                        parsed.parent = le_u64(content, 0)? & 0xffff_ffff_ffff;
+                        dos_name = namespace == 2;
+                    }
+                }
+                // The unnamed $DATA stream
+                0x80 if unnamed => {
+                    if non_resident {
+                        parsed.size = le_u64(attr, 0x30)?;
+                    } else {
+                        parsed.size = content.len() as u64;
+                        parsed.resident = Some(content.to_vec());
+                    }
+                }
+                _ => {}
+            }
+            offset += len;
+        }
+        Some(parsed)
+    }
+}
+
+/// Unallocated file records of an NTFS `$MFT`, with their paths
+///
+/// Paths follow the parent references of every record, free ones
+/// included; those whose parent is gone go under `/$OrphanFiles`.
+fn deleted_mft_records(mut mft: Vec<u8>) -> Vec<(String, MftRecord)> {
+    let record_size = le_u32(&mft, 0x1c)
+        .map(|size| size as usize)
+        .filter(|size| size.is_power_of_two() && *size >= 512)
+        .unwrap_or(1024);
+    let records: HashMap<u64, MftRecord> = mft
+        .chunks_exact_mut(record_size)
+        .enumerate()
+        .filter_map(|(i, record)| MftRecord::parse(i as u64, record))
+        .map(|record| (record.number, record))
+        .collect();
+
+    let path = |record: &MftRecord| {
+        let mut parts = vec![record
+            .name
+            .clone()
+            .unwrap_or_else(|| format!("mft-{}", record.number))];
+        let mut parent = record.parent;
+        while parent != MFT_ROOT && parts.len() < 256 {
+            match records
+                .get(&parent)
+                .and_then(|p| p.name.clone().map(|name| (name, p.parent)))
+            {
+                Some((name, next)) => {
+                    parts.push(name);
+                    parent = next;
+                }
+                None => {
+                    parts.push("$OrphanFiles".to_string());
+                    break;
+                }
+            }
+        }
+        parts.reverse();
+        format!("/{}", parts.join("/"))
+    };
+
+    let mut deleted: Vec<(String, MftRecord)> = records
+        .values()
+        .filter(|record| !record.in_use && !record.directory && record.size > 0)
+        .map(|record| (path(record), record.clone()))
+        .collect();
+    deleted.sort_by_key(|(_, record)| record.number);
+    deleted
+}
+
+/// One line of a TSK 3.x bodyfile:
+/// `MD5|name|inode|mode_as_string|UID|GID|size|atime|mtime|ctime|crtime`
+///
+/// Times are Unix seconds; 0 means the filesystem does not record it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BodyfileEntry {
+    pub md5: String,
+    pub name: String,
+    /// Metadata address, e.g. `1234` or NTFS's `5-128-1`
+    pub inode: String,
+    /// `ls -l` style mode, e.g. `r/rrw-r--r--`
+    pub mode: String,
+    pub uid: u32,
+    pub gid: u32,
+    pub size: u64,
+    pub atime: i64,
+    pub mtime: i64,
+    pub ctime: i64,
+    pub crtime: i64,
+}
+
+impl BodyfileEntry {
+    /// Parse a bodyfile line; names may themselves contain `|`
+    pub fn parse(line: &str) -> Option<Self> {
+        let fields: Vec<&str> = line.split('|').collect();
+        if fields.len() < 11 {
+            return None;
+        }
+        let tail = &fields[fields.len() - 9..];
+        let number = |i: usize| tail[i].trim().parse::<i64>().ok();
+        Some(Self {
+            md5: fields[0].to_string(),
+            name: fields[1..fields.len() - 9].join("|"),
+            inode: tail[0].to_string(),
+            mode: tail[1].to_string(),
+            uid: tail[2].trim().parse().ok()?,
+            gid: tail[3].trim().parse().ok()?,
+            size: tail[4].trim().parse().ok()?,
+            atime: number(5)?,
+            mtime: number(6)?,
+            ctime: number(7)?,
+            crtime: number(8)?,
+        })
+    }
+
+    /// The entry as a bodyfile line
+    pub fn to_line(&self) -> String {
+        format!(
+            "{}|{}|{}|{}|{}|{}|{}|{}|{}|{}|{}",
+            self.md5,
+            self.name,
+            self.inode,
+            self.mode,
+            self.uid,
+            self.gid,
+            self.size,
+            self.atime,
+            self.mtime,
+            self.ctime,
+            self.crtime
+        )
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -208,4 +594,106 @@ mod tests {
         let mut g = Guestfs::new().unwrap();
         // API structure tests
     }
+
+    /// A 1 KiB MFT record with a file name and resident data
+    fn mft_record(flags: u16, parent: u64, name: &str, data: &[u8]) -> Vec<u8> {
+        let mut record = vec![0u8; 1024];
+        record[..4].copy_from_slice(b"FILE");
+        record[0x4..0x6].copy_from_slice(&0x30u16.to_le_bytes());
+        record[0x6..0x8].copy_from_slice(&3u16.to_le_bytes());
+        record[0x14..0x16].copy_from_slice(&0x38u16.to_le_bytes());
+        record[0x16..0x18].copy_from_slice(&flags.to_le_bytes());
+        record[0x1c..0x20].copy_from_slice(&1024u32.to_le_bytes());
+
+        let mut attrs = Vec::new();
+        let mut attr = |kind: u32, content: &[u8]| {
+            let len = (0x18 + content.len() + 7) & !7;
+            let mut a = vec![0u8; len];
+            a[..4].copy_from_slice(&kind.to_le_bytes());
+            a[4..8].copy_from_slice(&(len as u32).to_le_bytes());
+            a[0x10..0x14].copy_from_slice(&(content.len() as u32).to_le_bytes());
+            a[0x14..0x16].copy_from_slice(&0x18u16.to_le_bytes());
+            a[0x18..0x18 + content.len()].copy_from_slice(content);
+            attrs.extend(a);
+        };
+        let mut info = vec![0u8; 0x48];
+        // 2024-01-01T00:00:00Z
+        let mtime = (1_704_067_200 + FILETIME_EPOCH) as u64 * 10_000_000;
+        info[0x8..0x10].copy_from_slice(&mtime.to_le_bytes());
+        attr(0x10, &info);
+        let units: Vec<u16> = name.encode_utf16().collect();
+        let mut file_name = vec![0u8; 0x42 + 2 * units.len()];
+        file_name[..8].copy_from_slice(&(parent | 3 << 48).to_le_bytes());
+        file_name[0x40] = units.len() as u8;
+        file_name[0x41] = 1;
+        for (i, unit) in units.iter().enumerate() {
+            file_name[0x42 + 2 * i..0x44 + 2 * i].copy_from_slice(&unit.to_le_bytes());
+        }
+        attr(0x30, &file_name);
+        if !data.is_empty() {
+            attr(0x80, data);
+        }
+        attrs.extend(0xffff_ffffu32.to_le_bytes());
+        record[0x38..0x38 + attrs.len()].copy_from_slice(&attrs);
+
+        // Update sequence: the last two bytes of each sector move to the array
+        record[0x30..0x32].copy_from_slice(&7u16.to_le_bytes());
+        for i in 1..3 {
+            let end = i * 512 - 2;
+            let (saved, check) = (record[end..end + 2].to_vec(), 7u16.to_le_bytes());
+            record[0x30 + 2 * i..0x32 + 2 * i].copy_from_slice(&saved);
+            record[end..end + 2].copy_from_slice(&check);
+        }
+        record
+    }
+
+    #[test]
+    fn test_mft_record() {
+        let mut record = mft_record(0, 40, "notes.txt", b"meet at noon");
+        let parsed = MftRecord::parse(41, &mut record).unwrap();
+        assert!(!parsed.in_use && !parsed.directory);
+        assert_eq!(parsed.name.as_deref(), Some("notes.txt"));
+        assert_eq!((parsed.parent, parsed.size), (40, 12));
+        assert_eq!(parsed.mtime, 1_704_067_200);
+        assert_eq!(parsed.resident.as_deref(), Some(&b"meet at noon"[..]));
+
+        // A torn write leaves a sector without the update sequence number
+        let mut torn = mft_record(0, 40, "notes.txt", b"x");
+        torn[1022] ^= 0xff;
+        assert!(MftRecord::parse(41, &mut torn).is_none());
+    }
+
+    #[test]
+    fn test_deleted_mft_records() {
+        let mut mft = Vec::new();
+        for number in 0..44u64 {
+            mft.extend(match number {
+                5 => mft_record(0x3, 5, ".", b""),
+                40 => mft_record(0x3, 5, "Users", b""),
+                41 => mft_record(0x1, 40, "kept.txt", b"kept"),
+                42 => mft_record(0, 40, "gone.txt", b"gone"),
+                43 => mft_record(0, 39, "lost.txt", b"lost"),
+                _ => vec![0u8; 1024],
+            });
+        }
+        let deleted = deleted_mft_records(mft);
+        let paths: Vec<&str> = deleted.iter().map(|(path, _)| path.as_str()).collect();
+        assert_eq!(paths, ["/Users/gone.txt", "/$OrphanFiles/lost.txt"]);
+        assert_eq!(deleted[0].1.number, 42);
+    }
+
+    #[test]
+    fn test_bodyfile_entry() {
+        let line = "0|/var/log/a|b.log|1317|r/rrw-r-----|0|4|5120|1700000300|1700000200|1700000100|1699990000";
+        let entry = BodyfileEntry::parse(line).unwrap();
+        assert_eq!(entry.name, "/var/log/a|b.log");
+        assert_eq!(entry.inode, "1317");
+        assert_eq!((entry.uid, entry.gid, entry.size), (0, 4, 5120));
+        assert_eq!(
+            (entry.atime, entry.mtime, entry.ctime, entry.crtime),
+            (1700000300, 1700000200, 1700000100, 1699990000)
+        );
+        assert_eq!(entry.to_line(), line);
+        assert!(BodyfileEntry::parse("0|/etc|2").is_none());
+    }
 }
@@ -5,8 +5,40 @@
 
 use crate::core::{Error, Result};
 use crate::guestfs::Guestfs;
+use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
 
+/// Trigger elements of a Task Scheduler definition
+const TASK_TRIGGERS: [&str; 9] = [
+    "BootTrigger",
+    "LogonTrigger",
+    "TimeTrigger",
+    "CalendarTrigger",
+    "IdleTrigger",
+    "EventTrigger",
+    "RegistrationTrigger",
+    "SessionStateChangeTrigger",
+    "WnfStateChangeTrigger",
+];
+
+/// Windows scheduled task (from %SystemRoot%\System32\Tasks)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WindowsScheduledTask {
+    /// Task path, e.g. "\Microsoft\Windows\Defrag\ScheduledDefrag"
+    pub path: String,
+    pub enabled: bool,
+    pub author: Option<String>,
+    pub description: Option<String>,
+    /// Command line of the first Exec action, or the COM handler class
+    pub command: Option<String>,
+    /// Trigger kinds, e.g. "Logon", "Calendar"
+    pub triggers: Vec<String>,
+    /// User or group the task runs as
+    pub run_as: Option<String>,
+    /// Runs with highest privileges
+    pub elevated: bool,
+}
+
 impl Guestfs {
     /// Get Windows systemroot
     ///
@@ -39,9 +71,13 @@ impl Guestfs {
             eprintln!("guestfs: inspect_get_windows_current_control_set {}", root);
         }
 
-        // Default control set
-        // In a full implementation, this would read from the registry
-        Ok("ControlSet001".to_string())
+        // Select\Current in the SYSTEM hive; ControlSet001 if it can't be read
+        let control_set = self
+            .inspect_get_windows_system_hive(root)
+            .and_then(|hive| self.resolve_guest_path(&hive))
+            .and_then(|path| super::windows_registry::get_current_control_set(&path));
+
+        Ok(control_set.unwrap_or_else(|_| "ControlSet001".to_string()))
     }
 
     /// List Windows drivers
@@ -71,6 +107,40 @@ impl Guestfs {
         Ok(drivers)
     }
 
+    /// List Windows scheduled tasks
+    ///
+    /// Parses the task definitions under %SystemRoot%\System32\Tasks.
+    pub fn inspect_list_windows_scheduled_tasks(
+        &mut self,
+        root: &str,
+    ) -> Result<Vec<WindowsScheduledTask>> {
+        self.ensure_ready()?;
+
+        if self.verbose {
+            eprintln!("guestfs: inspect_list_windows_scheduled_tasks {}", root);
+        }
+
+        let systemroot = self.inspect_get_windows_systemroot(root)?;
+        let tasks_dir = format!("{}/System32/Tasks", systemroot);
+
+        if !self.is_dir(&tasks_dir)? {
+            return Ok(Vec::new());
+        }
+
+        let mut tasks = Vec::new();
+        for file in self.find(&tasks_dir)? {
+            let Ok(content) = self.read_file(&format!("{}{}", tasks_dir, file)) else {
+                continue;
+            };
+            if let Some(task) = parse_scheduled_task(&file, &decode_task_file(&content)) {
+                tasks.push(task);
+            }
+        }
+
+        tasks.sort_by_key(|task| task.path.to_lowercase());
+        Ok(tasks)
+    }
+
     /// Get Windows software hive path
     ///
     pub fn inspect_get_windows_software_hive(&mut self, root: &str) -> Result<String> {
@@ -246,6 +316,119 @@ impl Guestfs {
     }
 }
 
+/// Decode a task file (UTF-16 with BOM as written by Task Scheduler, or UTF-8)
+fn decode_task_file(content: &[u8]) -> String {
+    let utf16 = |bytes: &[u8], from: fn([u8; 2]) -> u16| {
+        let units: Vec<u16> = bytes
+            .chunks_exact(2)
+            .map(|pair| from([pair[0], pair[1]]))
+            .collect();
+        String::from_utf16_lossy(&units)
+    };
+
+    match content {
+        [0xFF, 0xFE, rest @ ..] => utf16(rest, u16::from_le_bytes),
+        [0xFE, 0xFF, rest @ ..] => utf16(rest, u16::from_be_bytes),
+        [0xEF, 0xBB, 0xBF, rest @ ..] => String::from_utf8_lossy(rest).into_owned(),
+        _ => String::from_utf8_lossy(content).into_owned(),
+    }
+}
+
+/// Parse a Task Scheduler XML definition
+///
+/// `file` is the path below the Tasks directory, used when the definition
+/// has no URI. Returns `None` for files that are not task definitions.
+fn parse_scheduled_task(file: &str, xml: &str) -> Option<WindowsScheduledTask> {
+    let task = xml_element(xml, "Task")?;
+    let registration = xml_element(task, "RegistrationInfo").unwrap_or_default();
+    let settings = xml_element(task, "Settings").unwrap_or_default();
+    let principal = xml_element(task, "Principal").unwrap_or_default();
+    let actions = xml_element(task, "Actions").unwrap_or_default();
+    let triggers = xml_element(task, "Triggers").unwrap_or_default();
+
+    let path = xml_text(registration, "URI").unwrap_or_else(|| file.replace('/', "\\"));
+
+    let command = match xml_element(actions, "Exec") {
+        Some(exec) => xml_text(exec, "Command").map(|command| match xml_text(exec, "Arguments") {
+            Some(arguments) => format!("{} {}", command, arguments),
+            None => command,
+        }),
+        None => xml_element(actions, "ComHandler")
+            .and_then(|handler| xml_text(handler, "ClassId"))
+            .map(|class| format!("COM handler {}", class)),
+    };
+
+    let run_as = xml_text(principal, "UserId")
+        .or_else(|| xml_text(principal, "GroupId"))
+        .map(|account| match account.as_str() {
+            "S-1-5-18" => "SYSTEM".to_string(),
+            "S-1-5-19" => "LOCAL SERVICE".to_string(),
+            "S-1-5-20" => "NETWORK SERVICE".to_string(),
+            "S-1-5-32-544" => "Administrators".to_string(),
+            "S-1-5-32-545" => "Users".to_string(),
+            _ => account,
+        });
+
+    Some(WindowsScheduledTask {
+        path,
+        enabled: xml_text(settings, "Enabled").is_none_or(|enabled| enabled != "false"),
+        author: xml_text(registration, "Author"),
+        description: xml_text(registration, "Description"),
+        command,
+        triggers: TASK_TRIGGERS
+            .iter()
+            .filter(|trigger| xml_element(triggers, trigger).is_some())
+            .map(|trigger| trigger.trim_end_matches("Trigger").to_string())
+            .collect(),
+        run_as,
+        elevated: xml_text(principal, "RunLevel").as_deref() == Some("HighestAvailable"),
+    })
+}
+
+/// Content of the first `<tag>` element (empty for `<tag/>`)
+fn xml_element<'a>(xml: &'a str, tag: &str) -> Option<&'a str> {
+    let open = format!("<{}", tag);
+    let close = format!("</{}>", tag);
+
+    let mut offset = 0;
+    while let Some(pos) = xml[offset..].find(&open) {
+        let start = offset + pos + open.len();
+        offset = start;
+
+        // Skip longer tag names sharing the prefix (<Exec> vs <ExecutionTimeLimit>)
+        let rest = &xml[start..];
+        match rest.chars().next() {
+            Some('>') | Some(' ') | Some('\t') | Some('\r') | Some('\n') | Some('/') => {}
+            _ => continue,
+        }
+
+        let end_of_tag = rest.find('>')?;
+        if rest[..end_of_tag].ends_with('/') {
+            return Some("");
+        }
+        let content = &rest[end_of_tag + 1..];
+        return content.find(&close).map(|end| &content[..end]);
+    }
+
+    None
+}
+
+/// Trimmed, unescaped text of the first `<tag>` element, if not empty
+fn xml_text(xml: &str, tag: &str) -> Option<String> {
+    let text = xml_element(xml, tag)?.trim();
+    if text.is_empty() {
+        return None;
+    }
+
+    Some(
+        text.replace("&lt;", "<")
+            .replace("&gt;", ">")
+            .replace("&quot;", "\"")
+            .replace("&apos;", "'")
+            .replace("&amp;", "&"),
+    )
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -255,4 +438,67 @@ mod tests {
         let mut g = Guestfs::new().unwrap();
         // API structure tests
     }
+
+    const DEFRAG_TASK: &str = r#"<?xml version="1.0" encoding="UTF-16"?>
+<Task version="1.6" xmlns="http://schemas.microsoft.com/windows/2004/02/mit/task">
+  <RegistrationInfo>
+    <Author>Microsoft Corporation</Author>
+    <Description>Optimizes local storage drives.</Description>
+    <URI>\Microsoft\Windows\Defrag\ScheduledDefrag</URI>
+  </RegistrationInfo>
+  <Principals>
+    <Principal id="LocalSystem">
+      <UserId>S-1-5-18</UserId>
+      <RunLevel>HighestAvailable</RunLevel>
+    </Principal>
+  </Principals>
+  <Settings>
+    <Enabled>false</Enabled>
+    <ExecutionTimeLimit>PT72H</ExecutionTimeLimit>
+  </Settings>
+  <Triggers>
+    <CalendarTrigger>
+      <Enabled>true</Enabled>
+    </CalendarTrigger>
+    <LogonTrigger/>
+  </Triggers>
+  <Actions Context="LocalSystem">
+    <Exec>
+      <Command>%windir%\system32\defrag.exe</Command>
+      <Arguments>-c -h -o -$ &amp; more</Arguments>
+    </Exec>
+  </Actions>
+</Task>"#;
+
+    #[test]
+    fn test_parse_scheduled_task() {
+        let task =
+            parse_scheduled_task("/Microsoft/Windows/Defrag/ScheduledDefrag", DEFRAG_TASK).unwrap();
+
+        assert_eq!(task.path, "\\Microsoft\\Windows\\Defrag\\ScheduledDefrag");
+        assert!(!task.enabled);
+        assert_eq!(task.author.as_deref(), Some("Microsoft Corporation"));
+        assert_eq!(
+            task.command.as_deref(),
+            Some("%windir%\\system32\\defrag.exe -c -h -o -$ & more")
+        );
+        assert_eq!(task.triggers, vec!["Logon", "Calendar"]);
+        assert_eq!(task.run_as.as_deref(), Some("SYSTEM"));
+        assert!(task.elevated);
+    }
+
+    #[test]
+    fn test_parse_scheduled_task_utf16() {
+        let xml = "<Task><Actions><ComHandler><ClassId>{A}</ClassId></ComHandler></Actions></Task>";
+        let mut content = vec![0xFF, 0xFE];
+        content.extend(xml.encode_utf16().flat_map(|unit| unit.to_le_bytes()));
+
+        let task = parse_scheduled_task("/Updater", &decode_task_file(&content)).unwrap();
+        assert_eq!(task.path, "\\Updater");
+        assert!(task.enabled);
+        assert_eq!(task.command.as_deref(), Some("COM handler {A}"));
+        assert!(task.triggers.is_empty());
+
+        assert!(parse_scheduled_task("/desktop.ini", "[.ShellClassInfo]").is_none());
+    }
 }
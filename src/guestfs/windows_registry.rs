@@ -6,8 +6,155 @@
 //! registry parsing in future versions.
 
 use crate::core::{Error, Result};
+use std::collections::HashMap;
 use std::path::Path;
 
+/// Uninstall keys in the SOFTWARE hive (native and 32-bit applications)
+const UNINSTALL_KEYS: [&[&str]; 2] = [
+    &["Microsoft", "Windows", "CurrentVersion", "Uninstall"],
+    &[
+        "Wow6432Node",
+        "Microsoft",
+        "Windows",
+        "CurrentVersion",
+        "Uninstall",
+    ],
+];
+
+/// `CurrentState` of an installed Component Based Servicing package
+const CBS_STATE_INSTALLED: u32 = 0x70;
+
+/// Seconds between the FILETIME epoch (1601) and the Unix epoch
+const FILETIME_UNIX_OFFSET_SECS: i64 = 11_644_473_600;
+
+/// SAM account control flags (`F` value of a user)
+const ACB_DISABLED: u16 = 0x0001;
+const ACB_PWNOTREQ: u16 = 0x0004;
+const ACB_PWNOEXP: u16 = 0x0200;
+const ACB_AUTOLOCK: u16 = 0x0400;
+
+/// Size of the offset table before the data of a user's `V` value
+const SAM_USER_V_HEADER: usize = 0xCC;
+
+/// Size of the header before the data of a group's `C` value
+const SAM_ALIAS_C_HEADER: usize = 0x34;
+
+/// Registry value types read during inspection
+#[derive(Debug, Clone, PartialEq)]
+enum RegValue {
+    String(String),
+    Dword(u32),
+    Binary(Vec<u8>),
+}
+
+/// A registry key with its values and, if requested, its direct subkeys
+#[derive(Debug, Clone, Default)]
+struct RegKey {
+    name: String,
+    values: HashMap<String, RegValue>,
+    subkeys: Vec<RegKey>,
+}
+
+impl RegKey {
+    /// Non-empty string value
+    fn string(&self, name: &str) -> Option<&str> {
+        match self.values.get(name) {
+            Some(RegValue::String(data)) if !data.trim().is_empty() => Some(data.trim()),
+            _ => None,
+        }
+    }
+
+    /// DWORD value
+    fn dword(&self, name: &str) -> Option<u32> {
+        match self.values.get(name) {
+            Some(RegValue::Dword(data)) => Some(*data),
+            _ => None,
+        }
+    }
+
+    /// Binary value
+    fn binary(&self, name: &str) -> Option<&[u8]> {
+        match self.values.get(name) {
+            Some(RegValue::Binary(data)) => Some(data),
+            _ => None,
+        }
+    }
+}
+
+/// Read the key at `path` below the root of a hive
+///
+/// Returns `None` if a key along the path does not exist.
+fn read_key(hive_path: &Path, path: &[&str], with_subkeys: bool) -> Result<Option<RegKey>> {
+    use nt_hive2::{Hive, HiveParseMode, RegistryValue};
+    use std::cell::RefCell;
+    use std::fs::File;
+    use std::rc::Rc;
+
+    if !hive_path.exists() {
+        return Err(Error::NotFound(format!(
+            "Registry hive not found: {}",
+            hive_path.display()
+        )));
+    }
+
+    let file = File::open(hive_path)
+        .map_err(|e| Error::CommandFailed(format!("Failed to open hive: {}", e)))?;
+    let mut hive = Hive::new(file, HiveParseMode::NormalWithBaseBlock)
+        .map_err(|e| Error::CommandFailed(format!("Failed to parse hive: {:?}", e)))?;
+    let root_key = hive
+        .root_key_node()
+        .map_err(|e| Error::CommandFailed(format!("Failed to get root key: {:?}", e)))?;
+
+    let mut key = Rc::new(RefCell::new(root_key));
+    for name in path {
+        let next = match key.borrow().subkey(name, &mut hive) {
+            Ok(Some(next)) => next,
+            _ => return Ok(None),
+        };
+        key = next;
+    }
+
+    let convert = |value: &RegistryValue| match value {
+        RegistryValue::RegSZ(data) | RegistryValue::RegExpandSZ(data) => {
+            Some(RegValue::String(data.clone()))
+        }
+        RegistryValue::RegDWord(data) => Some(RegValue::Dword(*data)),
+        RegistryValue::RegBinary(data) => Some(RegValue::Binary(data.clone())),
+        _ => None,
+    };
+
+    let key_ref = key.borrow();
+    let mut result = RegKey {
+        name: key_ref.name().to_string(),
+        ..Default::default()
+    };
+    for kv in key_ref.values() {
+        if let Some(value) = convert(kv.value()) {
+            result.values.insert(kv.name().to_string(), value);
+        }
+    }
+
+    if with_subkeys {
+        if let Ok(subkeys) = key_ref.subkeys(&mut hive) {
+            for subkey in subkeys.iter() {
+                let subkey_ref = subkey.borrow();
+                let mut child = RegKey {
+                    name: subkey_ref.name().to_string(),
+                    ..Default::default()
+                };
+                for kv in subkey_ref.values() {
+                    if let Some(value) = convert(kv.value()) {
+                        child.values.insert(kv.name().to_string(), value);
+                    }
+                }
+                result.subkeys.push(child);
+            }
+        }
+    }
+
+    Ok(Some(result))
+}
+
 /// Windows application information
 #[derive(Debug, Clone)]
 pub struct WindowsApp {
@@ -15,6 +162,7 @@ pub struct WindowsApp {
     pub version: String,
     pub publisher: String,
     pub install_location: Option<String>,
+    pub install_date: Option<String>,
 }
 
 /// Windows service information
@@ -24,6 +172,11 @@ pub struct WindowsSvc {
     pub display_name: String,
     pub start_type: String,
     pub image_path: String,
+    /// Kernel driver, file system driver, or service process
+    pub service_type: String,
+    /// Account the service runs as (ObjectName)
+    pub account: Option<String>,
+    pub description: Option<String>,
 }
 
 /// Windows network adapter information
@@ -42,212 +195,131 @@ pub struct WindowsNetAdapter {
 /// Reads from SOFTWARE\Microsoft\Windows\CurrentVersion\Uninstall
 /// and SOFTWARE\Wow6432Node\Microsoft\Windows\CurrentVersion\Uninstall (for 32-bit apps on 64-bit Windows)
 pub fn parse_installed_software(hive_path: &Path) -> Result<Vec<WindowsApp>> {
-    use nt_hive2::{Hive, HiveParseMode, RegistryValue};
-    use std::fs::File;
-
-    // Verify hive exists
-    if !hive_path.exists() {
-        return Err(Error::NotFound(format!(
-            "SOFTWARE hive not found: {}",
-            hive_path.display()
-        )));
-    }
-
-    // Read hive file
-    let file = File::open(hive_path)
-        .map_err(|e| Error::CommandFailed(format!("Failed to open hive: {}", e)))?;
-
-    // Parse hive
-    let mut hive = Hive::new(file, HiveParseMode::NormalWithBaseBlock)
-        .map_err(|e| Error::CommandFailed(format!("Failed to parse hive: {:?}", e)))?;
-
-    let mut applications = Vec::new();
-
-    // Get root key
-    let root_key = hive
-        .root_key_node()
-        .map_err(|e| Error::CommandFailed(format!("Failed to get root key: {:?}", e)))?;
-
-    // Navigate to Uninstall key: Microsoft\Windows\CurrentVersion\Uninstall
-    let microsoft_key = match root_key.subkey("Microsoft", &mut hive) {
-        Ok(Some(key)) => key,
-        _ => return Ok(applications), // No Microsoft key
-    };
-
-    let windows_key = match microsoft_key.borrow().subkey("Windows", &mut hive) {
-        Ok(Some(key)) => key,
-        _ => return Ok(applications), // No Windows key
-    };
-
-    let current_version_key = match windows_key.borrow().subkey("CurrentVersion", &mut hive) {
-        Ok(Some(key)) => key,
-        _ => return Ok(applications), // No CurrentVersion key
-    };
-
-    let uninstall_key = match current_version_key.borrow().subkey("Uninstall", &mut hive) {
-        Ok(Some(key)) => key,
-        _ => return Ok(applications), // No Uninstall key
-    };
-
-    // Iterate through subkeys (each represents an installed application)
-    let uninstall_borrowed = uninstall_key.borrow();
-    let subkeys_result = uninstall_borrowed.subkeys(&mut hive);
-    let subkeys_ref = match subkeys_result {
-        Ok(ref_vec) => ref_vec,
-        Err(_) => return Ok(applications),
-    };
-
-    for app_key in (&*subkeys_ref).iter() {
-        let app_key_ref = app_key.borrow();
-
-        // Extract application information from values
-        let mut name = String::new();
-        let mut version = String::new();
-        let mut publisher = String::new();
-        let mut install_location = None;
-
-        for kv in app_key_ref.values() {
-            match kv.name().as_ref() {
-                "DisplayName" => {
-                    if let RegistryValue::RegSZ(data) | RegistryValue::RegExpandSZ(data) = kv.value() {
-                        name = data.clone();
-                    }
+    let mut applications: Vec<WindowsApp> = Vec::new();
+
+    for path in UNINSTALL_KEYS {
+        if let Some(uninstall) = read_key(hive_path, path, true)? {
+            for app in apps_from_uninstall_key(&uninstall) {
+                // Applications registering both views are listed once
+                if !applications
+                    .iter()
+                    .any(|a| a.name == app.name && a.version == app.version)
+                {
+                    applications.push(app);
                 }
-                "DisplayVersion" => {
-                    if let RegistryValue::RegSZ(data) | RegistryValue::RegExpandSZ(data) = kv.value() {
-                        version = data.clone();
-                    }
-                }
-                "Publisher" => {
-                    if let RegistryValue::RegSZ(data) | RegistryValue::RegExpandSZ(data) = kv.value() {
-                        publisher = data.clone();
-                    }
-                }
-                "InstallLocation" => {
-                    if let RegistryValue::RegSZ(data) | RegistryValue::RegExpandSZ(data) = kv.value() {
-                        if !data.is_empty() {
-                            install_location = Some(data.clone());
-                        }
-                    }
-                }
-                _ => {}
             }
         }
-
-        // Only add if we have a display name
-        if !name.is_empty() {
-            applications.push(WindowsApp {
-                name,
-                version,
-                publisher,
-                install_location,
-            });
-        }
     }
 
+    applications.sort_by_key(|app| app.name.to_lowercase());
     Ok(applications)
 }
 
-/// Parse Windows services from SYSTEM hive
+/// Applications of an Uninstall key, as listed in "Programs and Features"
 ///
-/// Reads from SYSTEM\ControlSet001\Services
-pub fn parse_windows_services(hive_path: &Path) -> Result<Vec<WindowsSvc>> {
-    use nt_hive2::{Hive, HiveParseMode, RegistryValue};
-    use std::fs::File;
+/// Entries without a display name, system components, and updates of other
+/// products (`ParentKeyName`) are skipped.
+fn apps_from_uninstall_key(uninstall: &RegKey) -> Vec<WindowsApp> {
+    uninstall
+        .subkeys
+        .iter()
+        .filter(|key| key.dword("SystemComponent") != Some(1))
+        .filter(|key| key.string("ParentKeyName").is_none())
+        .filter_map(|key| {
+            Some(WindowsApp {
+                name: key.string("DisplayName")?.to_string(),
+                version: key.string("DisplayVersion").unwrap_or_default().to_string(),
+                publisher: key.string("Publisher").unwrap_or_default().to_string(),
+                install_location: key.string("InstallLocation").map(str::to_string),
+                install_date: key.string("InstallDate").map(format_install_date),
+            })
+        })
+        .collect()
+}
 
-    // Verify hive exists
-    if !hive_path.exists() {
-        return Err(Error::NotFound(format!(
-            "SYSTEM hive not found: {}",
-            hive_path.display()
-        )));
+/// Format a `YYYYMMDD` install date as `YYYY-MM-DD` (other formats are kept)
+fn format_install_date(date: &str) -> String {
+    if date.len() == 8 && date.bytes().all(|b| b.is_ascii_digit()) {
+        format!("{}-{}-{}", &date[..4], &date[4..6], &date[6..])
+    } else {
+        date.to_string()
     }
+}
 
-    // Read hive file
-    let file = File::open(hive_path)
-        .map_err(|e| Error::CommandFailed(format!("Failed to open hive: {}", e)))?;
-
-    // Parse hive
-    let mut hive = Hive::new(file, HiveParseMode::NormalWithBaseBlock)
-        .map_err(|e| Error::CommandFailed(format!("Failed to parse hive: {:?}", e)))?;
-
-    let mut services = Vec::new();
-
-    // Get root key
-    let root_key = hive
-        .root_key_node()
-        .map_err(|e| Error::CommandFailed(format!("Failed to get root key: {:?}", e)))?;
-
-    // Navigate to Services: ControlSet001\Services
-    let controlset_key = match root_key.subkey("ControlSet001", &mut hive) {
-        Ok(Some(key)) => key,
-        _ => return Ok(services), // No ControlSet001 key
-    };
-
-    let services_key = match controlset_key.borrow().subkey("Services", &mut hive) {
-        Ok(Some(key)) => key,
-        _ => return Ok(services), // No Services key
-    };
-
-    // Iterate through service subkeys
-    let services_borrowed = services_key.borrow();
-    let subkeys_result = services_borrowed.subkeys(&mut hive);
-    let subkeys_ref = match subkeys_result {
-        Ok(ref_vec) => ref_vec,
-        Err(_) => return Ok(services),
-    };
-
-    for svc_key in (&*subkeys_ref).iter() {
-        let svc_key_ref = svc_key.borrow();
-        let svc_name = svc_key_ref.name().to_string();
-
-        // Extract service information
-        let mut display_name = svc_name.clone();
-        let mut start_type = String::from("Unknown");
-        let mut image_path = String::new();
+/// Parse Windows services from SYSTEM hive
+///
+/// Reads from SYSTEM\<CurrentControlSet>\Services
+pub fn parse_windows_services(hive_path: &Path) -> Result<Vec<WindowsSvc>> {
+    let control_set = get_current_control_set(hive_path)?;
 
-        for kv in svc_key_ref.values() {
-            match kv.name().as_ref() {
-                "DisplayName" => {
-                    if let RegistryValue::RegSZ(data) | RegistryValue::RegExpandSZ(data) = kv.value() {
-                        display_name = data.clone();
-                    }
-                }
-                "Start" => {
-                    // Start type is a DWORD:
-                    // 0 = Boot, 1 = System, 2 = Automatic, 3 = Manual, 4 = Disabled
-                    if let RegistryValue::RegDWord(start_val) = kv.value() {
-                        start_type = match start_val {
-                            0 => "Boot".to_string(),
-                            1 => "System".to_string(),
-                            2 => "Automatic".to_string(),
-                            3 => "Manual".to_string(),
-                            4 => "Disabled".to_string(),
-                            _ => format!("Unknown({})", start_val),
-                        };
-                    }
-                }
-                "ImagePath" => {
-                    if let RegistryValue::RegSZ(data) | RegistryValue::RegExpandSZ(data) = kv.value() {
-                        image_path = data.clone();
-                    }
-                }
-                _ => {}
-            }
-        }
+    Ok(read_key(hive_path, &[&control_set, "Services"], true)?
+        .map(|services| services_from_key(&services))
+        .unwrap_or_default())
+}
 
-        // Only add services that have an image path (actual services, not just service groups)
-        if !image_path.is_empty() {
-            services.push(WindowsSvc {
-                name: svc_name,
-                display_name,
-                start_type,
+/// Services and drivers of a Services key
+///
+/// Keys without an ImagePath are service groups or leftover settings, not
+/// services, and are skipped.
+fn services_from_key(services: &RegKey) -> Vec<WindowsSvc> {
+    services
+        .subkeys
+        .iter()
+        .filter_map(|key| {
+            let image_path = key.string("ImagePath")?.to_string();
+            Some(WindowsSvc {
+                name: key.name.clone(),
+                display_name: key
+                    .string("DisplayName")
+                    .filter(|name| !name.starts_with('@'))
+                    .unwrap_or(&key.name)
+                    .to_string(),
+                start_type: key
+                    .dword("Start")
+                    .map(start_type_name)
+                    .unwrap_or_else(|| "Unknown".to_string()),
                 image_path,
-            });
-        }
+                service_type: key
+                    .dword("Type")
+                    .map(service_type_name)
+                    .unwrap_or_else(|| "Unknown".to_string()),
+                account: key.string("ObjectName").map(str::to_string),
+                // "@file.dll,-100" descriptions are resource references
+                description: key
+                    .string("Description")
+                    .filter(|description| !description.starts_with('@'))
+                    .map(str::to_string),
+            })
+        })
+        .collect()
+}
+
+/// Name of a service `Start` value
+fn start_type_name(start: u32) -> String {
+    match start {
+        0 => "Boot".to_string(),
+        1 => "System".to_string(),
+        2 => "Automatic".to_string(),
+        3 => "Manual".to_string(),
+        4 => "Disabled".to_string(),
+        _ => format!("Unknown({})", start),
     }
+}
 
-    Ok(services)
+/// Name of a service `Type` value
+fn service_type_name(service_type: u32) -> String {
+    match service_type {
+        0x1 => "Kernel Driver".to_string(),
+        0x2 => "File System Driver".to_string(),
+        0x4 => "Adapter".to_string(),
+        0x8 => "Recognizer Driver".to_string(),
+        // Per-user service templates (0x50, 0x60) are instantiated at logon
+        t if t & 0x40 != 0 => "User Service".to_string(),
+        // 0x100 marks services that may interact with the desktop
+        t if t & 0x10 != 0 => "Own Process".to_string(),
+        t if t & 0x20 != 0 => "Shared Process".to_string(),
+        _ => format!("Unknown({:#x})", service_type),
+    }
 }
 
 /// Parse network configuration from SYSTEM hive
@@ -391,119 +463,362 @@ pub fn parse_network_adapters(hive_path: &Path) -> Result<Vec<WindowsNetAdapter>
     Ok(adapters)
 }
 
+/// Windows version details
+#[derive(Debug, Clone, PartialEq)]
+pub struct WindowsOsInfo {
+    /// Product name (e.g., "Windows 11 Pro")
+    pub product_name: String,
+    /// Edition ID (e.g., "Professional")
+    pub edition: String,
+    /// Version as major.minor.build (e.g., "10.0.22631")
+    pub version: String,
+    /// Build number including the update revision (e.g., "22631.3007")
+    pub build_number: String,
+    /// Feature update (e.g., "23H2")
+    pub display_version: Option<String>,
+}
+
 /// Get Windows version from SOFTWARE hive
 ///
 /// Returns (product_name, version, edition)
 /// Reads from SOFTWARE\Microsoft\Windows NT\CurrentVersion
 pub fn get_windows_version(hive_path: &Path) -> Result<(String, String, String)> {
-    use nt_hive2::{Hive, HiveParseMode};
-    use std::fs::File;
+    let info = get_windows_os_info(hive_path)?;
+    Ok((info.product_name, info.version, info.edition))
+}
 
-    // Verify hive exists
-    if !hive_path.exists() {
-        return Err(Error::NotFound(format!(
-            "SOFTWARE hive not found: {}",
-            hive_path.display()
-        )));
+/// Get Windows version details from SOFTWARE hive
+///
+/// Reads from SOFTWARE\Microsoft\Windows NT\CurrentVersion
+pub fn get_windows_os_info(hive_path: &Path) -> Result<WindowsOsInfo> {
+    let current_version = read_key(
+        hive_path,
+        &["Microsoft", "Windows NT", "CurrentVersion"],
+        false,
+    )?
+    .ok_or_else(|| Error::NotFound("CurrentVersion key not found".to_string()))?;
+
+    Ok(os_info_from_key(&current_version))
+}
+
+fn os_info_from_key(key: &RegKey) -> WindowsOsInfo {
+    let build = key
+        .string("CurrentBuildNumber")
+        .or_else(|| key.string("CurrentBuild"))
+        .unwrap_or_default();
+
+    // Windows 10 and later have numeric version values; older releases
+    // only have CurrentVersion (e.g., "6.1" for Windows 7)
+    let major_minor = match key.dword("CurrentMajorVersionNumber") {
+        Some(major) => Some(format!(
+            "{}.{}",
+            major,
+            key.dword("CurrentMinorVersionNumber").unwrap_or(0)
+        )),
+        None => key.string("CurrentVersion").map(str::to_string),
+    };
+
+    let version = match (major_minor, build.is_empty()) {
+        (Some(major_minor), false) => format!("{}.{}", major_minor, build),
+        (Some(major_minor), true) => major_minor,
+        (None, false) => build.to_string(),
+        (None, true) => "Unknown".to_string(),
+    };
+
+    let build_number = match key.dword("UBR") {
+        Some(ubr) if !build.is_empty() => format!("{}.{}", build, ubr),
+        _ => build.to_string(),
+    };
+
+    // Windows 11 kept "Windows 10" in ProductName; builds from 22000 on are 11
+    let mut product_name = key.string("ProductName").unwrap_or("Windows").to_string();
+    if build.parse::<u32>().is_ok_and(|b| b >= 22000) && product_name.starts_with("Windows 10") {
+        product_name = product_name.replacen("Windows 10", "Windows 11", 1);
     }
 
-    // Read hive file
-    let file = File::open(hive_path)
-        .map_err(|e| Error::CommandFailed(format!("Failed to open hive: {}", e)))?;
+    WindowsOsInfo {
+        product_name,
+        edition: key.string("EditionID").unwrap_or("Unknown").to_string(),
+        version,
+        build_number,
+        display_version: key
+            .string("DisplayVersion")
+            .or_else(|| key.string("ReleaseId"))
+            .map(str::to_string),
+    }
+}
 
-    // Parse hive
-    let mut hive = Hive::new(file, HiveParseMode::NormalWithBaseBlock)
-        .map_err(|e| Error::CommandFailed(format!("Failed to parse hive: {:?}", e)))?;
+/// Get the control set Windows boots with from SYSTEM hive
+///
+/// Reads SYSTEM\Select\Current (e.g., "ControlSet001")
+pub fn get_current_control_set(hive_path: &Path) -> Result<String> {
+    let current = read_key(hive_path, &["Select"], false)?
+        .and_then(|select| select.dword("Current"))
+        .unwrap_or(1);
 
-    // Navigate to CurrentVersion key
-    let root_key = hive
-        .root_key_node()
-        .map_err(|e| Error::CommandFailed(format!("Failed to get root key: {:?}", e)))?;
+    Ok(format!("ControlSet{:03}", current))
+}
 
-    // Path: Microsoft\Windows NT\CurrentVersion
-    // Navigate step by step
-    let microsoft_key = root_key
-        .subkey("Microsoft", &mut hive)
-        .map_err(|e| Error::CommandFailed(format!("Failed to find Microsoft key: {:?}", e)))?
-        .ok_or_else(|| Error::NotFound("Microsoft key not found".to_string()))?;
-
-    let windows_nt_key = microsoft_key
-        .borrow()
-        .subkey("Windows NT", &mut hive)
-        .map_err(|e| Error::CommandFailed(format!("Failed to find Windows NT key: {:?}", e)))?
-        .ok_or_else(|| Error::NotFound("Windows NT key not found".to_string()))?;
-
-    let current_version_key = windows_nt_key
-        .borrow()
-        .subkey("CurrentVersion", &mut hive)
-        .map_err(|e| Error::CommandFailed(format!("Failed to find CurrentVersion key: {:?}", e)))?
-        .ok_or_else(|| Error::NotFound("CurrentVersion key not found".to_string()))?;
-
-    // Read values
-    let mut product_name = String::from("Windows");
-    let mut version = String::from("Unknown");
-    let mut edition = String::from("Unknown");
-    let mut build = String::new();
-    let mut major_version = String::new();
-    let mut minor_version = String::new();
-
-    // Get all values from the CurrentVersion key
-    let key_ref = current_version_key.borrow();
-    let values = key_ref.values();
-
-    // Iterate through values to find the ones we need
-    for kv in values {
-        // Get value name
-        let name = kv.name();
-
-        match name.as_ref() {
-            "ProductName" => {
-                // Read string value (e.g., "Windows 10 Pro", "Windows 11 Home")
-                use nt_hive2::RegistryValue;
-                if let RegistryValue::RegSZ(data) | RegistryValue::RegExpandSZ(data) = kv.value() {
-                    product_name = data.clone();
-                }
-            }
-            "EditionID" => {
-                // Read string value (e.g., "Professional", "Home", "Enterprise")
-                use nt_hive2::RegistryValue;
-                if let RegistryValue::RegSZ(data) | RegistryValue::RegExpandSZ(data) = kv.value() {
-                    edition = data.clone();
-                }
-            }
-            "CurrentBuild" => {
-                // Read string value (e.g., "19045", "22631")
-                use nt_hive2::RegistryValue;
-                if let RegistryValue::RegSZ(data) | RegistryValue::RegExpandSZ(data) = kv.value() {
-                    build = data.clone();
-                }
-            }
-            "CurrentMajorVersionNumber" => {
-                // Read DWORD value
-                use nt_hive2::RegistryValue;
-                if let RegistryValue::RegDWord(data) = kv.value() {
-                    major_version = data.to_string();
-                }
-            }
-            "CurrentMinorVersionNumber" => {
-                // Read DWORD value
-                use nt_hive2::RegistryValue;
-                if let RegistryValue::RegDWord(data) = kv.value() {
-                    minor_version = data.to_string();
-                }
+/// Get the computer name from SYSTEM hive
+///
+/// Reads Control\ComputerName\ComputerName of the current control set,
+/// falling back to the TCP/IP host name.
+pub fn get_computer_name(hive_path: &Path) -> Result<String> {
+    let control_set = get_current_control_set(hive_path)?;
+
+    let computer_name = read_key(
+        hive_path,
+        &[&control_set, "Control", "ComputerName", "ComputerName"],
+        false,
+    )?
+    .and_then(|key| key.string("ComputerName").map(str::to_string));
+    if let Some(name) = computer_name {
+        return Ok(name);
+    }
+
+    read_key(
+        hive_path,
+        &[&control_set, "Services", "Tcpip", "Parameters"],
+        false,
+    )?
+    .and_then(|key| key.string("Hostname").map(str::to_string))
+    .ok_or_else(|| Error::NotFound("Computer name not set in SYSTEM hive".to_string()))
+}
+
+/// Windows local user account from the SAM hive
+#[derive(Debug, Clone)]
+pub struct WindowsLocalUser {
+    pub name: String,
+    /// Relative ID (500 for Administrator, 1000 and up for created users)
+    pub rid: u32,
+    /// Account SID, if the machine SID is known
+    pub sid: Option<String>,
+    pub full_name: Option<String>,
+    pub comment: Option<String>,
+    pub disabled: bool,
+    pub locked_out: bool,
+    pub password_required: bool,
+    pub password_expires: bool,
+    /// Last logon time (UTC), `None` if the account never logged on
+    pub last_logon: Option<String>,
+    pub password_last_set: Option<String>,
+    pub logon_count: u16,
+    /// Local groups the account is a member of
+    pub groups: Vec<String>,
+}
+
+/// Windows local group from the SAM hive
+#[derive(Debug, Clone)]
+pub struct WindowsLocalGroup {
+    pub name: String,
+    pub rid: u32,
+    pub comment: Option<String>,
+    /// Member SIDs, with local accounts resolved to their user names
+    pub members: Vec<String>,
+}
+
+/// Parse local user accounts from SAM hive
+///
+/// Reads SAM\Domains\Account\Users, where each account key holds the
+/// account flags and logon times (F value) and the names (V value). Group
+/// membership comes from [`parse_local_groups`].
+pub fn parse_local_users(sam_path: &Path) -> Result<Vec<WindowsLocalUser>> {
+    let (mut users, groups) = read_sam(sam_path)?;
+
+    for group in &groups {
+        for user in users.iter_mut() {
+            if group.members.contains(&user.name) {
+                user.groups.push(group.name.clone());
             }
-            _ => {}
         }
     }
 
-    // Construct version string
-    if !major_version.is_empty() && !build.is_empty() {
-        version = format!("{}.{}.{}", major_version, minor_version, build);
-    } else if !build.is_empty() {
-        version = build;
+    Ok(users)
+}
+
+/// Parse local groups from SAM hive
+///
+/// Reads the built-in groups (Administrators, Users, ...) from
+/// SAM\Domains\Builtin\Aliases and groups created on the machine from
+/// SAM\Domains\Account\Aliases.
+pub fn parse_local_groups(sam_path: &Path) -> Result<Vec<WindowsLocalGroup>> {
+    read_sam(sam_path).map(|(_, groups)| groups)
+}
+
+fn read_sam(sam_path: &Path) -> Result<(Vec<WindowsLocalUser>, Vec<WindowsLocalGroup>)> {
+    let machine_sid = read_key(sam_path, &["SAM", "Domains", "Account"], false)?
+        .and_then(|account| account.binary("V").and_then(machine_sid));
+
+    let users = read_key(sam_path, &["SAM", "Domains", "Account", "Users"], true)?
+        .map(|key| users_from_key(&key, machine_sid.as_deref()))
+        .ok_or_else(|| Error::NotFound("No user accounts in SAM hive".to_string()))?;
+
+    let mut groups = Vec::new();
+    for domain in ["Builtin", "Account"] {
+        if let Some(aliases) = read_key(sam_path, &["SAM", "Domains", domain, "Aliases"], true)? {
+            groups.extend(groups_from_key(&aliases, &users));
+        }
+    }
+
+    Ok((users, groups))
+}
+
+/// Machine SID from the V value of SAM\Domains\Account
+///
+/// The value ends with the three sub-authorities after S-1-5-21.
+fn machine_sid(v: &[u8]) -> Option<String> {
+    let tail = v.get(v.len().checked_sub(12)?..)?;
+    let ids: Vec<String> = (0..3)
+        .filter_map(|i| read_u32(tail, i * 4))
+        .map(|id| id.to_string())
+        .collect();
+    Some(format!("S-1-5-21-{}", ids.join("-")))
+}
+
+/// Users from SAM\Domains\Account\Users, whose subkeys are named by RID
+fn users_from_key(users: &RegKey, machine_sid: Option<&str>) -> Vec<WindowsLocalUser> {
+    users
+        .subkeys
+        .iter()
+        .filter_map(|key| {
+            let rid = u32::from_str_radix(&key.name, 16).ok()?;
+            let v = key.binary("V")?;
+            let f = key.binary("F").unwrap_or_default();
+            let flags = read_u16(f, 0x38).unwrap_or(0);
+            let time = |offset| read_u64(f, offset).and_then(filetime_datetime);
+
+            Some(WindowsLocalUser {
+                name: sam_string(v, SAM_USER_V_HEADER, 0x0C)?,
+                rid,
+                sid: machine_sid.map(|sid| format!("{}-{}", sid, rid)),
+                full_name: sam_string(v, SAM_USER_V_HEADER, 0x18),
+                comment: sam_string(v, SAM_USER_V_HEADER, 0x24),
+                disabled: flags & ACB_DISABLED != 0,
+                locked_out: flags & ACB_AUTOLOCK != 0,
+                password_required: flags & ACB_PWNOTREQ == 0,
+                password_expires: flags & ACB_PWNOEXP == 0,
+                last_logon: time(0x08),
+                password_last_set: time(0x18),
+                logon_count: read_u16(f, 0x42).unwrap_or(0),
+                groups: Vec::new(),
+            })
+        })
+        .collect()
+}
+
+/// Groups from an Aliases key, whose subkeys are named by RID
+///
+/// Members that are local accounts are listed by user name, all others
+/// (domain accounts, well-known SIDs) by SID.
+fn groups_from_key(aliases: &RegKey, users: &[WindowsLocalUser]) -> Vec<WindowsLocalGroup> {
+    aliases
+        .subkeys
+        .iter()
+        .filter_map(|key| {
+            let rid = u32::from_str_radix(&key.name, 16).ok()?;
+            let c = key.binary("C")?;
+
+            let mut members = Vec::new();
+            let mut offset = read_u32(c, 0x28)? as usize + SAM_ALIAS_C_HEADER;
+            for _ in 0..read_u32(c, 0x30)? {
+                let Some((sid, len)) = parse_sid(c.get(offset..)?) else {
+                    break;
+                };
+                let user = users.iter().find(|user| match &user.sid {
+                    Some(user_sid) => *user_sid == sid,
+                    None => {
+                        sid.starts_with("S-1-5-21-") && sid.ends_with(&format!("-{}", user.rid))
+                    }
+                });
+                members.push(user.map_or(sid, |user| user.name.clone()));
+                offset += len;
+            }
+
+            Some(WindowsLocalGroup {
+                name: sam_string(c, SAM_ALIAS_C_HEADER, 0x10)?,
+                rid,
+                comment: sam_string(c, SAM_ALIAS_C_HEADER, 0x1C),
+                members,
+            })
+        })
+        .collect()
+}
+
+/// Parse a binary SID, returning its string form and length in bytes
+fn parse_sid(data: &[u8]) -> Option<(String, usize)> {
+    let revision = *data.first()?;
+    let count = *data.get(1)? as usize;
+    let len = 8 + count * 4;
+    let authority = data
+        .get(2..8)?
+        .iter()
+        .fold(0u64, |acc, byte| (acc << 8) | u64::from(*byte));
+
+    let mut sid = format!("S-{}-{}", revision, authority);
+    for i in 0..count {
+        sid.push_str(&format!("-{}", read_u32(data, 8 + i * 4)?));
     }
+    Some((sid, len))
+}
 
-    Ok((product_name, version, edition))
+/// Non-empty UTF-16 string of a SAM value
+///
+/// `entry` is the position of the string's (offset, length) pair; offsets
+/// are relative to the end of the value's header.
+fn sam_string(data: &[u8], header: usize, entry: usize) -> Option<String> {
+    let offset = read_u32(data, entry)? as usize + header;
+    let len = read_u32(data, entry + 4)? as usize;
+    let units: Vec<u16> = data
+        .get(offset..offset.checked_add(len)?)?
+        .chunks_exact(2)
+        .map(|pair| u16::from_le_bytes([pair[0], pair[1]]))
+        .collect();
+
+    let text = String::from_utf16_lossy(&units);
+    let text = text.trim_end_matches('\0').trim();
+    (!text.is_empty()).then(|| text.to_string())
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Option<u16> {
+    Some(u16::from_le_bytes(
+        data.get(offset..offset + 2)?.try_into().ok()?,
+    ))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Option<u32> {
+    Some(u32::from_le_bytes(
+        data.get(offset..offset + 4)?.try_into().ok()?,
+    ))
+}
+
+fn read_u64(data: &[u8], offset: usize) -> Option<u64> {
+    Some(u64::from_le_bytes(
+        data.get(offset..offset + 8)?.try_into().ok()?,
+    ))
+}
+
+/// Get user profile directories from SOFTWARE hive
+///
+/// Reads SOFTWARE\Microsoft\Windows NT\CurrentVersion\ProfileList and
+/// returns the ProfileImagePath of each profile keyed by SID.
+pub fn parse_profile_paths(hive_path: &Path) -> Result<HashMap<String, String>> {
+    let profiles = read_key(
+        hive_path,
+        &["Microsoft", "Windows NT", "CurrentVersion", "ProfileList"],
+        true,
+    )?;
+
+    Ok(profiles
+        .map(|key| {
+            key.subkeys
+                .iter()
+                .filter_map(|profile| {
+                    let path = profile.string("ProfileImagePath")?;
+                    Some((profile.name.clone(), path.to_string()))
+                })
+                .collect()
+        })
+        .unwrap_or_default())
 }
 
 /// Windows update/hotfix information
@@ -518,27 +833,122 @@ pub struct WindowsUpdateInfo {
 
 /// Parse installed Windows updates from registry
 ///
-/// Checks SOFTWARE hive for installed updates and hotfixes
+/// Reads installed packages from
+/// SOFTWARE\Microsoft\Windows\CurrentVersion\Component Based Servicing\Packages
+/// and hotfixes from SOFTWARE\Microsoft\Windows NT\CurrentVersion\HotFix
+/// (Windows XP and Server 2003). Each KB is listed once.
 pub fn parse_installed_updates(hive_path: &Path) -> Result<Vec<WindowsUpdateInfo>> {
-    // Verify hive exists
-    if !hive_path.exists() {
-        return Err(Error::NotFound(format!(
-            "SOFTWARE hive not found: {}",
-            hive_path.display()
-        )));
+    let mut updates = Vec::new();
+
+    let cbs_packages = read_key(
+        hive_path,
+        &[
+            "Microsoft",
+            "Windows",
+            "CurrentVersion",
+            "Component Based Servicing",
+            "Packages",
+        ],
+        true,
+    )?;
+    if let Some(packages) = cbs_packages {
+        updates.extend(updates_from_cbs_packages(&packages));
     }
 
-    // TODO: Parse registry keys:
-    // - SOFTWARE\Microsoft\Windows\CurrentVersion\Component Based Servicing\Packages
-    // - SOFTWARE\Microsoft\Windows NT\CurrentVersion\HotFix
+    let hotfix_key = read_key(
+        hive_path,
+        &["Microsoft", "Windows NT", "CurrentVersion", "HotFix"],
+        true,
+    )?;
+    if let Some(hotfixes) = hotfix_key {
+        updates.extend(hotfixes.subkeys.iter().filter_map(|key| {
+            let kb_number = kb_number(&key.name)?;
+            Some(WindowsUpdateInfo {
+                title: key
+                    .string("Description")
+                    .map(str::to_string)
+                    .unwrap_or_else(|| format!("Hotfix for {}", kb_number)),
+                kb_number,
+                description: key.name.clone(),
+                installed_date: "Unknown".to_string(),
+                update_type: "Hotfix".to_string(),
+            })
+        }));
+    }
 
-    Ok(vec![WindowsUpdateInfo {
-        kb_number: "Registry".to_string(),
-        title: "Update registry detected".to_string(),
-        description: "Full parsing pending".to_string(),
-        installed_date: "Unknown".to_string(),
-        update_type: "Registry".to_string(),
-    }])
+    let mut seen = std::collections::HashSet::new();
+    updates.retain(|update| seen.insert(update.kb_number.clone()));
+    Ok(updates)
+}
+
+/// Installed updates among Component Based Servicing packages
+///
+/// Package names carry the KB number, e.g.
+/// `Package_for_KB5034441~31bf3856ad364e35~amd64~~10.0.1.0`.
+fn updates_from_cbs_packages(packages: &RegKey) -> Vec<WindowsUpdateInfo> {
+    packages
+        .subkeys
+        .iter()
+        .filter(|key| {
+            key.dword("CurrentState")
+                .is_none_or(|state| state == CBS_STATE_INSTALLED)
+        })
+        .filter_map(|key| {
+            let kb_number = kb_number(&key.name)?;
+            let installed_date = match (key.dword("InstallTimeHigh"), key.dword("InstallTimeLow")) {
+                (Some(high), Some(low)) => filetime_date((u64::from(high) << 32) | u64::from(low)),
+                _ => None,
+            };
+
+            Some(WindowsUpdateInfo {
+                title: format!("Update for {}", kb_number),
+                kb_number,
+                description: key.name.clone(),
+                installed_date: installed_date.unwrap_or_else(|| "Unknown".to_string()),
+                update_type: key.string("ReleaseType").unwrap_or("Update").to_string(),
+            })
+        })
+        .collect()
+}
+
+/// Extract a KB number (e.g., "KB5034441") from a package or key name
+fn kb_number(name: &str) -> Option<String> {
+    let upper = name.to_ascii_uppercase();
+    let mut rest = upper.as_str();
+    while let Some(pos) = rest.find("KB") {
+        let digits: String = rest[pos + 2..]
+            .chars()
+            .take_while(|c| c.is_ascii_digit())
+            .collect();
+        if digits.len() >= 6 {
+            return Some(format!("KB{}", digits));
+        }
+        rest = &rest[pos + 2..];
+    }
+    None
+}
+
+/// Date (YYYY-MM-DD) of a Windows FILETIME
+fn filetime_date(filetime: u64) -> Option<String> {
+    let secs = (filetime / 10_000_000) as i64 - FILETIME_UNIX_OFFSET_SECS;
+    if secs <= 0 {
+        return None;
+    }
+    chrono::DateTime::from_timestamp(secs, 0).map(|t| t.format("%Y-%m-%d").to_string())
+}
+
+/// Date and time (UTC) of a Windows FILETIME
+///
+/// Zero and the maximum value both mean "never".
+fn filetime_datetime(filetime: u64) -> Option<String> {
+    if filetime == 0 || filetime >= i64::MAX as u64 {
+        return None;
+    }
+    let secs = (filetime / 10_000_000) as i64 - FILETIME_UNIX_OFFSET_SECS;
+    if secs <= 0 {
+        return None;
+    }
+    chrono::DateTime::from_timestamp(secs, 0).map(|t| t.format("%Y-%m-%d %H:%M:%S").to_string())
 }
 
 /// Parse CBS.log for component-based servicing updates
@@ -692,13 +1102,314 @@ pub fn parse_application_events(evtx_path: &Path) -> Result<Vec<WindowsEventEntr
     parse_evtx_file(evtx_path, 50)
 }
 
-// TODO: Full registry parsing implementation using nt_hive2
-// The nt_hive2 API requires:
-// 1. use nt_hive2::{Hive, SubPath, HiveParseMode};
-// 2. let mut hive = Hive::new(file, HiveParseMode::NormalWithBaseBlock)?;
-// 3. let root = hive.root_key_node()?;
-// 4. let key = root.subpath(path, &mut hive)?.unwrap();
-// 5. Handle Rc<RefCell<KeyNode>> return types
-// 6. Parse values with correct type conversions
-//
-// This will be implemented when we have Windows VM images for testing
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn key(name: &str, values: &[(&str, RegValue)]) -> RegKey {
+        RegKey {
+            name: name.to_string(),
+            values: values
+                .iter()
+                .map(|(name, value)| (name.to_string(), value.clone()))
+                .collect(),
+            subkeys: Vec::new(),
+        }
+    }
+
+    fn string(data: &str) -> RegValue {
+        RegValue::String(data.to_string())
+    }
+
+    #[test]
+    fn test_os_info_windows_11() {
+        let current_version = key(
+            "CurrentVersion",
+            &[
+                ("ProductName", string("Windows 10 Pro")),
+                ("EditionID", string("Professional")),
+                ("CurrentBuildNumber", string("22631")),
+                ("CurrentMajorVersionNumber", RegValue::Dword(10)),
+                ("CurrentMinorVersionNumber", RegValue::Dword(0)),
+                ("UBR", RegValue::Dword(3007)),
+                ("DisplayVersion", string("23H2")),
+            ],
+        );
+
+        let info = os_info_from_key(&current_version);
+        assert_eq!(info.product_name, "Windows 11 Pro");
+        assert_eq!(info.edition, "Professional");
+        assert_eq!(info.version, "10.0.22631");
+        assert_eq!(info.build_number, "22631.3007");
+        assert_eq!(info.display_version.as_deref(), Some("23H2"));
+    }
+
+    #[test]
+    fn test_os_info_windows_7() {
+        let current_version = key(
+            "CurrentVersion",
+            &[
+                ("ProductName", string("Windows 7 Enterprise")),
+                ("CurrentVersion", string("6.1")),
+                ("CurrentBuildNumber", string("7601")),
+            ],
+        );
+
+        let info = os_info_from_key(&current_version);
+        assert_eq!(info.product_name, "Windows 7 Enterprise");
+        assert_eq!(info.edition, "Unknown");
+        assert_eq!(info.version, "6.1.7601");
+        assert_eq!(info.build_number, "7601");
+        assert_eq!(info.display_version, None);
+    }
+
+    #[test]
+    fn test_apps_from_uninstall_key() {
+        let mut uninstall = key("Uninstall", &[]);
+        uninstall.subkeys = vec![
+            key(
+                "{7-Zip}",
+                &[
+                    ("DisplayName", string("7-Zip 23.01")),
+                    ("DisplayVersion", string("23.01")),
+                    ("Publisher", string("Igor Pavlov")),
+                    ("InstallDate", string("20240115")),
+                ],
+            ),
+            key(
+                "Runtime",
+                &[
+                    ("DisplayName", string("Hidden Runtime")),
+                    ("SystemComponent", RegValue::Dword(1)),
+                ],
+            ),
+            key(
+                "KB123456",
+                &[
+                    ("DisplayName", string("Update for Office")),
+                    ("ParentKeyName", string("Office16")),
+                ],
+            ),
+            key("NoName", &[("DisplayVersion", string("1.0"))]),
+        ];
+
+        let apps = apps_from_uninstall_key(&uninstall);
+        assert_eq!(apps.len(), 1);
+        assert_eq!(apps[0].name, "7-Zip 23.01");
+        assert_eq!(apps[0].publisher, "Igor Pavlov");
+        assert_eq!(apps[0].install_date.as_deref(), Some("2024-01-15"));
+    }
+
+    #[test]
+    fn test_services_from_key() {
+        let mut services = key("Services", &[]);
+        services.subkeys = vec![
+            key(
+                "Spooler",
+                &[
+                    (
+                        "DisplayName",
+                        string("@%systemroot%\\system32\\spoolsv.exe,-1"),
+                    ),
+                    ("ImagePath", string("%SystemRoot%\\System32\\spoolsv.exe")),
+                    ("Start", RegValue::Dword(2)),
+                    ("Type", RegValue::Dword(0x110)),
+                    ("ObjectName", string("LocalSystem")),
+                ],
+            ),
+            key(
+                "disk",
+                &[
+                    ("ImagePath", string("System32\\drivers\\disk.sys")),
+                    ("Start", RegValue::Dword(0)),
+                    ("Type", RegValue::Dword(0x1)),
+                ],
+            ),
+            key("Audio", &[("Start", RegValue::Dword(3))]),
+        ];
+
+        let services = services_from_key(&services);
+        assert_eq!(services.len(), 2);
+
+        assert_eq!(services[0].name, "Spooler");
+        assert_eq!(services[0].display_name, "Spooler");
+        assert_eq!(services[0].start_type, "Automatic");
+        assert_eq!(services[0].service_type, "Own Process");
+        assert_eq!(services[0].account.as_deref(), Some("LocalSystem"));
+
+        assert_eq!(services[1].start_type, "Boot");
+        assert_eq!(services[1].service_type, "Kernel Driver");
+        assert_eq!(services[1].account, None);
+    }
+
+    #[test]
+    fn test_updates_from_cbs_packages() {
+        let mut packages = key("Packages", &[]);
+        packages.subkeys = vec![
+            key(
+                "Package_for_KB5034441~31bf3856ad364e35~amd64~~10.0.1.0",
+                &[
+                    ("CurrentState", RegValue::Dword(CBS_STATE_INSTALLED)),
+                    ("InstallTimeHigh", RegValue::Dword(0x01da_4b6c)),
+                    ("InstallTimeLow", RegValue::Dword(0)),
+                    ("ReleaseType", string("Security Update")),
+                ],
+            ),
+            key(
+                "Package_for_KB5001716~31bf3856ad364e35~amd64~~10.0.1.0",
+                &[("CurrentState", RegValue::Dword(0x05))],
+            ),
+            key("Microsoft-Windows-Foundation-Package~31bf3856ad364e35", &[]),
+        ];
+
+        let updates = updates_from_cbs_packages(&packages);
+        assert_eq!(updates.len(), 1);
+        assert_eq!(updates[0].kb_number, "KB5034441");
+        assert_eq!(updates[0].update_type, "Security Update");
+        assert_eq!(updates[0].installed_date, "2024-01-20");
+    }
+
+    #[test]
+    fn test_kb_number() {
+        assert_eq!(kb_number("KB4012212").as_deref(), Some("KB4012212"));
+        assert_eq!(
+            kb_number("Package_for_RollupFix~31bf3856ad364e35~amd64~~22621.3007.1.6"),
+            None
+        );
+        assert_eq!(
+            kb_number("Package_1_for_kb5034204~31bf3856ad364e35").as_deref(),
+            Some("KB5034204")
+        );
+    }
+
+    /// SAM value with `header` bytes of (offset, length) pairs followed by
+    /// the UTF-16 strings
+    fn sam_value(header: usize, strings: &[(usize, &str)]) -> Vec<u8> {
+        let mut data = vec![0u8; header];
+        for (entry, text) in strings {
+            let bytes: Vec<u8> = text.encode_utf16().flat_map(u16::to_le_bytes).collect();
+            let offset = (data.len() - header) as u32;
+            data[*entry..*entry + 4].copy_from_slice(&offset.to_le_bytes());
+            data[*entry + 4..*entry + 8].copy_from_slice(&(bytes.len() as u32).to_le_bytes());
+            data.extend(bytes);
+        }
+        data
+    }
+
+    fn sid_bytes(authority: u8, sub_authorities: &[u32]) -> Vec<u8> {
+        let mut data = vec![1, sub_authorities.len() as u8, 0, 0, 0, 0, 0, authority];
+        for id in sub_authorities {
+            data.extend(id.to_le_bytes());
+        }
+        data
+    }
+
+    #[test]
+    fn test_local_users_and_groups() {
+        let machine = "S-1-5-21-1004336348-1177238915-682003330";
+
+        let mut admin_f = vec![0u8; 0x50];
+        admin_f[0x38..0x3A].copy_from_slice(&(ACB_DISABLED | 0x0010).to_le_bytes());
+        let mut alice_f = vec![0u8; 0x50];
+        alice_f[0x08..0x10].copy_from_slice(&133_500_000_000_000_000u64.to_le_bytes());
+        alice_f[0x38..0x3A].copy_from_slice(&(ACB_PWNOEXP | 0x0010).to_le_bytes());
+        alice_f[0x42..0x44].copy_from_slice(&7u16.to_le_bytes());
+
+        let mut users_key = key("Users", &[]);
+        users_key.subkeys = vec![
+            key(
+                "000001F4",
+                &[
+                    ("F", RegValue::Binary(admin_f)),
+                    (
+                        "V",
+                        RegValue::Binary(sam_value(
+                            SAM_USER_V_HEADER,
+                            &[(0x0C, "Administrator"), (0x24, "Built-in account")],
+                        )),
+                    ),
+                ],
+            ),
+            key(
+                "000003E9",
+                &[
+                    ("F", RegValue::Binary(alice_f)),
+                    (
+                        "V",
+                        RegValue::Binary(sam_value(
+                            SAM_USER_V_HEADER,
+                            &[(0x0C, "alice"), (0x18, "Alice Example")],
+                        )),
+                    ),
+                ],
+            ),
+            key("Names", &[]),
+        ];
+
+        let users = users_from_key(&users_key, Some(machine));
+        assert_eq!(users.len(), 2);
+        let admin = &users[0];
+        assert_eq!((admin.name.as_str(), admin.rid), ("Administrator", 500));
+        assert!(admin.disabled);
+        assert_eq!(admin.comment.as_deref(), Some("Built-in account"));
+        assert_eq!(admin.last_logon, None);
+        let alice = &users[1];
+        assert_eq!(alice.sid.as_deref(), Some(&*format!("{}-1001", machine)));
+        assert_eq!(alice.full_name.as_deref(), Some("Alice Example"));
+        assert!(!alice.disabled && !alice.password_expires);
+        assert_eq!(alice.last_logon.as_deref(), Some("2024-01-17 21:20:00"));
+        assert_eq!(alice.logon_count, 7);
+
+        let mut c = sam_value(
+            SAM_ALIAS_C_HEADER,
+            &[(0x10, "Administrators"), (0x1C, "Complete access")],
+        );
+        let members_offset = (c.len() - SAM_ALIAS_C_HEADER) as u32;
+        c[0x28..0x2C].copy_from_slice(&members_offset.to_le_bytes());
+        c[0x30..0x34].copy_from_slice(&2u32.to_le_bytes());
+        c.extend(sid_bytes(5, &[21, 1004336348, 1177238915, 682003330, 1001]));
+        c.extend(sid_bytes(5, &[4]));
+
+        let mut aliases = key("Aliases", &[]);
+        aliases.subkeys = vec![
+            key("00000220", &[("C", RegValue::Binary(c))]),
+            key("Members", &[]),
+        ];
+
+        let groups = groups_from_key(&aliases, &users);
+        assert_eq!(groups.len(), 1);
+        assert_eq!(
+            (groups[0].name.as_str(), groups[0].rid),
+            ("Administrators", 544)
+        );
+        assert_eq!(
+            groups[0].members,
+            vec!["alice".to_string(), "S-1-5-4".to_string()]
+        );
+    }
+
+    #[test]
+    fn test_machine_sid() {
+        let mut v = vec![0u8; 0x40];
+        for id in [1004336348u32, 1177238915, 682003330] {
+            v.extend(id.to_le_bytes());
+        }
+        assert_eq!(
+            machine_sid(&v).as_deref(),
+            Some("S-1-5-21-1004336348-1177238915-682003330")
+        );
+        assert_eq!(machine_sid(&[0u8; 4]), None);
+        assert_eq!(
+            parse_sid(&sid_bytes(5, &[32, 544])),
+            Some(("S-1-5-32-544".to_string(), 16))
+        );
+    }
+
+    #[test]
+    fn test_missing_hive() {
+        let path = Path::new("/nonexistent/config/SOFTWARE");
+        assert!(get_windows_os_info(path).is_err());
+        assert!(parse_installed_updates(path).is_err());
+        assert!(parse_local_users(Path::new("/nonexistent/config/SAM")).is_err());
+    }
+}
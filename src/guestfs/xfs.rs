@@ -4,6 +4,7 @@
 //! This implementation provides XFS-specific functionality.
 
 use crate::core::{Error, Result};
+use crate::disk::Xfs;
 use crate::guestfs::Guestfs;
 use std::process::Command;
 
@@ -222,6 +223,26 @@ impl Guestfs {
 
         Ok(String::from_utf8_lossy(&output.stdout).to_string())
     }
+
+    /// Open the XFS filesystem on `device` of the first drive with the pure
+    /// Rust reader
+    ///
+    /// Needs no launch and mounts nothing. The v5 format current xfsprogs
+    /// makes, with reflink and bigtime, is read too.
+    pub fn xfs_open(&self, device: &str) -> Result<Xfs> {
+        let (reader, offset) = self.open_partition(device)?;
+        Xfs::from_reader(reader, offset)
+    }
+
+    /// Read a file of the XFS filesystem on `device`, without mounting
+    ///
+    pub fn xfs_read_file(&mut self, device: &str, path: &str) -> Result<Vec<u8>> {
+        if self.verbose {
+            eprintln!("guestfs: xfs_read_file {} {}", device, path);
+        }
+
+        self.xfs_open(device)?.read_file(path)
+    }
 }
 
 #[cfg(test)]
@@ -233,4 +254,13 @@ mod tests {
         let mut g = Guestfs::new().unwrap();
         // API structure tests
     }
+
+    #[test]
+    fn test_xfs_read_file_needs_a_drive() {
+        let mut g = Guestfs::new().unwrap();
+        assert!(matches!(
+            g.xfs_read_file("/dev/sda1", "/etc/os-release"),
+            Err(Error::InvalidState(_))
+        ));
+    }
 }
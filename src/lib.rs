@@ -39,6 +39,10 @@
 //! - `disk` - Pure Rust disk image, partition, and filesystem handling
 //! - `export` - Report generation in various formats (HTML with Chart.js, PDF, Markdown)
 //! - `guestfs` - GuestFS-compatible API for disk inspection and manipulation
+//! - `lint` - Syntax checks for guest configuration files
+//! - `oci` - Push and pull disk images as OCI registry artifacts
+//! - `plan` - Fix plan types and application
+//! - `publish` - Upload converted images to OpenStack Glance and Proxmox VE
 //! - `detectors` - Guest OS detection
 //! - `fixers` - Guest OS repair operations
 //! - `cli` - Command-line interface
@@ -48,11 +52,19 @@ pub mod core;
 pub mod disk;
 pub mod export;
 pub mod guestfs;
+pub mod lint;
+pub mod plan;
 
 // Optional modules
 #[cfg(feature = "guest-inspect")]
 pub mod detectors;
 
+#[cfg(feature = "oci")]
+pub mod oci;
+
+#[cfg(feature = "publish")]
+pub mod publish;
+
 #[cfg(feature = "python-bindings")]
 pub mod python;
 
@@ -0,0 +1,209 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! crontab checks
+
+use super::{content_lines, LintIssue};
+
+const SPECIALS: &[&str] = &[
+    "@reboot",
+    "@yearly",
+    "@annually",
+    "@monthly",
+    "@weekly",
+    "@daily",
+    "@midnight",
+    "@hourly",
+];
+
+const MONTHS: &[&str] = &[
+    "jan", "feb", "mar", "apr", "may", "jun", "jul", "aug", "sep", "oct", "nov", "dec",
+];
+
+const DAYS: &[&str] = &["sun", "mon", "tue", "wed", "thu", "fri", "sat"];
+
+/// A time field: name, range and accepted names
+struct Field {
+    name: &'static str,
+    min: u32,
+    max: u32,
+    names: &'static [&'static str],
+}
+
+const FIELDS: [Field; 5] = [
+    Field {
+        name: "minute",
+        min: 0,
+        max: 59,
+        names: &[],
+    },
+    Field {
+        name: "hour",
+        min: 0,
+        max: 23,
+        names: &[],
+    },
+    Field {
+        name: "day of month",
+        min: 1,
+        max: 31,
+        names: &[],
+    },
+    Field {
+        name: "month",
+        min: 1,
+        max: 12,
+        names: MONTHS,
+    },
+    Field {
+        name: "day of week",
+        min: 0,
+        max: 7,
+        names: DAYS,
+    },
+];
+
+impl Field {
+    fn value(&self, s: &str) -> Option<u32> {
+        if let Some(pos) = self.names.iter().position(|n| n.eq_ignore_ascii_case(s)) {
+            // Names count from the first value of the range
+            return Some(self.min + pos as u32);
+        }
+        s.parse().ok().filter(|v| (self.min..=self.max).contains(v))
+    }
+
+    /// Validate one field such as `*/5`, `1-5` or `mon,wed,fri`
+    fn check(&self, spec: &str) -> bool {
+        spec.split(',').all(|item| {
+            let (range, step) = match item.split_once('/') {
+                Some((range, step)) => (range, Some(step)),
+                None => (item, None),
+            };
+            if step.is_some_and(|s| !s.parse::<u32>().is_ok_and(|s| s > 0)) {
+                return false;
+            }
+            match range.split_once('-') {
+                _ if range == "*" => true,
+                Some((from, to)) => match (self.value(from), self.value(to)) {
+                    (Some(from), Some(to)) => from <= to,
+                    _ => false,
+                },
+                None => self.value(range).is_some(),
+            }
+        })
+    }
+}
+
+fn is_env_assignment(line: &str) -> bool {
+    line.split_once('=').is_some_and(|(name, _)| {
+        let name = name.trim();
+        !name.is_empty() && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+    })
+}
+
+pub fn lint(content: &str, system: bool) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+
+    for (line_no, line) in content_lines(content) {
+        if is_env_assignment(line) {
+            continue;
+        }
+
+        let mut rest = line;
+        let mut next = || {
+            let field = rest.split_whitespace().next()?;
+            rest = rest[field.len()..].trim_start();
+            Some(field)
+        };
+
+        let first = next().unwrap_or_default();
+        if first.starts_with('@') {
+            if !SPECIALS.contains(&first) {
+                issues.push(LintIssue::error(
+                    line_no,
+                    format!("unknown schedule {}", first),
+                ));
+                continue;
+            }
+        } else {
+            let mut fields = vec![first];
+            fields.extend((1..FIELDS.len()).filter_map(|_| next()));
+            if fields.len() < FIELDS.len() {
+                issues.push(LintIssue::error(
+                    line_no,
+                    "expected five time fields and a command",
+                ));
+                continue;
+            }
+            for (field, spec) in FIELDS.iter().zip(&fields) {
+                if !field.check(spec) {
+                    issues.push(LintIssue::error(
+                        line_no,
+                        format!("invalid {} field '{}'", field.name, spec),
+                    ));
+                }
+            }
+        }
+
+        if system && next().is_none() {
+            issues.push(LintIssue::error(line_no, "missing user field"));
+            continue;
+        }
+        if rest.is_empty() {
+            issues.push(LintIssue::error(line_no, "missing command"));
+            continue;
+        }
+        if rest.replace("\\%", "").contains('%') {
+            issues.push(LintIssue::warning(
+                line_no,
+                "unescaped '%' in the command is turned into a newline",
+            ));
+        }
+    }
+
+    if !content.is_empty() && !content.ends_with('\n') {
+        let last = content.lines().count();
+        issues.push(LintIssue::error(
+            last,
+            "missing newline at the end of the file; cron ignores the last line",
+        ));
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_crontab() {
+        let content = "SHELL=/bin/sh\n\
+            MAILTO=\"\"\n\
+            */15 * * * * /usr/local/bin/poll\n\
+            0 3 1-7 jan-mar mon,fri /usr/bin/backup --full\n\
+            @reboot /usr/bin/warmup\n\
+            30 2 * * 0 date +\\%F > /tmp/stamp\n";
+        assert_eq!(lint(content, false), vec![]);
+
+        assert_eq!(
+            lint("17 * * * * root cd / && run-parts /etc/cron.hourly\n", true),
+            vec![]
+        );
+    }
+
+    #[test]
+    fn test_broken_crontab() {
+        let content = "60 * * * * /bin/true\n\
+            * * * *\n\
+            @weekdays /bin/true\n\
+            */0 5-1 * * * /bin/true\n\
+            0 0 * * * date +%F";
+        let lines: Vec<_> = lint(content, false)
+            .iter()
+            .map(|i| i.line.unwrap())
+            .collect();
+        assert_eq!(lines, vec![1, 2, 3, 4, 4, 5, 5]);
+
+        // System crontabs need a user
+        assert_eq!(lint("@daily /bin/true\n", true).len(), 1);
+    }
+}
@@ -0,0 +1,132 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! /etc/fstab checks
+
+use super::{content_lines, LintIssue};
+use std::collections::HashSet;
+
+/// Device tags accepted in the first field
+const TAGS: &[&str] = &["UUID=", "LABEL=", "PARTUUID=", "PARTLABEL=", "ID="];
+
+pub fn lint(content: &str) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+    let mut mount_points = HashSet::new();
+
+    for (line_no, line) in content_lines(content) {
+        let fields: Vec<&str> = line.split_whitespace().collect();
+        if fields.len() < 4 {
+            issues.push(LintIssue::error(
+                line_no,
+                format!(
+                    "expected device, mount point, type and options, found {} field(s)",
+                    fields.len()
+                ),
+            ));
+            continue;
+        }
+        if fields.len() > 6 {
+            issues.push(LintIssue::error(
+                line_no,
+                format!("expected at most 6 fields, found {}", fields.len()),
+            ));
+        }
+
+        let (device, mount_point, fs_type, options) = (fields[0], fields[1], fields[2], fields[3]);
+
+        if let Some(tag) = TAGS.iter().find(|tag| device.starts_with(*tag)) {
+            let value = &device[tag.len()..];
+            if value.trim_matches('"').is_empty() {
+                issues.push(LintIssue::error(
+                    line_no,
+                    format!("empty {} device tag", tag),
+                ));
+            } else if *tag == "UUID="
+                && !value
+                    .trim_matches('"')
+                    .chars()
+                    .all(|c| c.is_ascii_hexdigit() || c == '-')
+            {
+                issues.push(LintIssue::error(
+                    line_no,
+                    format!("malformed UUID '{}'", value),
+                ));
+            }
+        }
+
+        let is_swap = fs_type == "swap";
+        if !mount_point.starts_with('/') && !matches!(mount_point, "none" | "swap") {
+            issues.push(LintIssue::error(
+                line_no,
+                format!("mount point '{}' is not an absolute path", mount_point),
+            ));
+        } else if mount_point.starts_with('/') && !mount_points.insert(mount_point.to_string()) {
+            issues.push(LintIssue::error(
+                line_no,
+                format!("duplicate mount point {}", mount_point),
+            ));
+        }
+        if is_swap && mount_point.starts_with('/') {
+            issues.push(LintIssue::warning(
+                line_no,
+                "swap entries normally use 'none' as the mount point",
+            ));
+        }
+
+        if options.split(',').any(str::is_empty) {
+            issues.push(LintIssue::error(
+                line_no,
+                format!("empty mount option in '{}'", options),
+            ));
+        }
+
+        if let Some(dump) = fields.get(4) {
+            if dump.parse::<u32>().is_err() {
+                issues.push(LintIssue::error(
+                    line_no,
+                    format!("dump field '{}' is not a number", dump),
+                ));
+            }
+        }
+        if let Some(pass) = fields.get(5) {
+            match pass.parse::<u32>() {
+                Ok(2) if mount_point == "/" => issues.push(LintIssue::warning(
+                    line_no,
+                    "the root filesystem should have fsck pass 1",
+                )),
+                Ok(0..=2) => {}
+                _ => issues.push(LintIssue::error(
+                    line_no,
+                    format!("fsck pass '{}' must be 0, 1 or 2", pass),
+                )),
+            }
+        }
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_fstab() {
+        let content = "# /etc/fstab\n\
+            UUID=0a3407de-014b-458b-b5c1-848e92a327a3 / ext4 defaults 0 1\n\
+            LABEL=boot /boot xfs defaults 0 2\n\
+            /dev/mapper/swap none swap sw 0 0\n\
+            tmpfs /tmp tmpfs nosuid,nodev\n";
+        assert!(lint(content).is_empty());
+    }
+
+    #[test]
+    fn test_broken_fstab() {
+        let content = "UUID= / ext4 defaults 0 1\n\
+            /dev/sdb1 data ext4 defaults,,noatime 0 5\n\
+            /dev/sdc1 /srv\n\
+            /dev/sdd1 / xfs defaults 0 1\n";
+        let issues = lint(content);
+        let lines: Vec<_> = issues.iter().map(|i| i.line.unwrap()).collect();
+        assert_eq!(lines, vec![1, 2, 2, 2, 3, 4]);
+        assert!(issues.iter().all(LintIssue::is_error));
+    }
+}
@@ -0,0 +1,332 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Syntax checks for common guest configuration files
+//!
+//! A typo in `/etc/fstab`, `sudoers` or a systemd unit can leave a guest
+//! unbootable or locked out. These linters catch the usual mistakes without
+//! running anything in the guest: `guestctl lint` runs them on demand, and
+//! plan application and the shell's `edit` refuse edits that introduce
+//! errors.
+
+mod crontab;
+mod fstab;
+mod nginx;
+mod sshd;
+mod structured;
+mod sudoers;
+mod systemd;
+
+use serde::{Deserialize, Serialize};
+use std::fmt;
+use std::path::Path;
+
+/// How serious a lint finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Severity {
+    /// Questionable but accepted by the consuming program
+    Warning,
+    /// Rejected by the consuming program, or breaks it
+    Error,
+}
+
+impl Severity {
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Warning => "warning",
+            Self::Error => "error",
+        }
+    }
+}
+
+/// A single lint finding
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LintIssue {
+    /// 1-based line number, if the finding is tied to a line
+    pub line: Option<usize>,
+    pub severity: Severity,
+    pub message: String,
+}
+
+impl LintIssue {
+    pub fn error(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            line: Some(line),
+            severity: Severity::Error,
+            message: message.into(),
+        }
+    }
+
+    pub fn warning(line: usize, message: impl Into<String>) -> Self {
+        Self {
+            line: Some(line),
+            severity: Severity::Warning,
+            message: message.into(),
+        }
+    }
+
+    pub fn is_error(&self) -> bool {
+        self.severity == Severity::Error
+    }
+}
+
+impl fmt::Display for LintIssue {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.line {
+            Some(line) => write!(
+                f,
+                "line {}: {}: {}",
+                line,
+                self.severity.as_str(),
+                self.message
+            ),
+            None => write!(f, "{}: {}", self.severity.as_str(), self.message),
+        }
+    }
+}
+
+/// Configuration file formats that can be linted
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "kebab-case")]
+pub enum FileKind {
+    Fstab,
+    SshdConfig,
+    Sudoers,
+    Nginx,
+    SystemdUnit,
+    /// Drop-in fragment (`foo.service.d/*.conf`) extending a unit
+    SystemdDropIn,
+    /// Per-user crontab (no user field)
+    Crontab,
+    /// `/etc/crontab` and `/etc/cron.d/*` (with a user field)
+    SystemCrontab,
+    Yaml,
+    Json,
+    Toml,
+}
+
+/// Systemd unit file suffixes
+const UNIT_SUFFIXES: &[&str] = &[
+    "service",
+    "socket",
+    "timer",
+    "mount",
+    "automount",
+    "swap",
+    "target",
+    "path",
+    "slice",
+    "scope",
+];
+
+impl FileKind {
+    /// All kinds, in the order they are documented
+    pub const ALL: &'static [FileKind] = &[
+        Self::Fstab,
+        Self::SshdConfig,
+        Self::Sudoers,
+        Self::Nginx,
+        Self::SystemdUnit,
+        Self::SystemdDropIn,
+        Self::Crontab,
+        Self::SystemCrontab,
+        Self::Yaml,
+        Self::Json,
+        Self::Toml,
+    ];
+
+    pub fn as_str(&self) -> &str {
+        match self {
+            Self::Fstab => "fstab",
+            Self::SshdConfig => "sshd-config",
+            Self::Sudoers => "sudoers",
+            Self::Nginx => "nginx",
+            Self::SystemdUnit => "systemd-unit",
+            Self::SystemdDropIn => "systemd-dropin",
+            Self::Crontab => "crontab",
+            Self::SystemCrontab => "system-crontab",
+            Self::Yaml => "yaml",
+            Self::Json => "json",
+            Self::Toml => "toml",
+        }
+    }
+
+    /// Parse a kind name as printed by [`FileKind::as_str`]
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::ALL.iter().copied().find(|kind| kind.as_str() == name)
+    }
+
+    /// Guess the format of a guest file from its path
+    pub fn detect(path: &str) -> Option<Self> {
+        let p = Path::new(path);
+        let name = p.file_name()?.to_str()?;
+        let extension = p.extension().and_then(|e| e.to_str()).unwrap_or("");
+        let parent = p
+            .parent()
+            .and_then(|d| d.to_str())
+            .unwrap_or("")
+            .trim_end_matches('/');
+
+        match path {
+            "/etc/fstab" => return Some(Self::Fstab),
+            "/etc/sudoers" => return Some(Self::Sudoers),
+            "/etc/crontab" => return Some(Self::SystemCrontab),
+            _ => {}
+        }
+
+        if name == "sshd_config" || (parent == "/etc/ssh/sshd_config.d" && extension == "conf") {
+            return Some(Self::SshdConfig);
+        }
+        if parent == "/etc/sudoers.d" {
+            // sudo skips files with a '.' or ending in '~' in sudoers.d
+            return (!name.contains('.') && !name.ends_with('~')).then_some(Self::Sudoers);
+        }
+        if parent == "/etc/cron.d" {
+            return Some(Self::SystemCrontab);
+        }
+        if path.starts_with("/var/spool/cron/") {
+            return Some(Self::Crontab);
+        }
+        if name == "nginx.conf" || (path.starts_with("/etc/nginx/") && extension == "conf") {
+            return Some(Self::Nginx);
+        }
+        if UNIT_SUFFIXES.contains(&extension) {
+            return Some(Self::SystemdUnit);
+        }
+        if extension == "conf"
+            && UNIT_SUFFIXES
+                .iter()
+                .any(|suffix| parent.ends_with(&format!(".{}.d", suffix)))
+        {
+            return Some(Self::SystemdDropIn);
+        }
+
+        match extension {
+            "yaml" | "yml" => Some(Self::Yaml),
+            "json" => Some(Self::Json),
+            "toml" => Some(Self::Toml),
+            _ => None,
+        }
+    }
+
+    /// Lint file content in this format
+    pub fn lint(&self, content: &str) -> Vec<LintIssue> {
+        match self {
+            Self::Fstab => fstab::lint(content),
+            Self::SshdConfig => sshd::lint(content),
+            Self::Sudoers => sudoers::lint(content),
+            Self::Nginx => nginx::lint(content),
+            Self::SystemdUnit => systemd::lint(content, false),
+            Self::SystemdDropIn => systemd::lint(content, true),
+            Self::Crontab => crontab::lint(content, false),
+            Self::SystemCrontab => crontab::lint(content, true),
+            Self::Yaml => structured::lint_yaml(content),
+            Self::Json => structured::lint_json(content),
+            Self::Toml => structured::lint_toml(content),
+        }
+    }
+}
+
+impl fmt::Display for FileKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.as_str())
+    }
+}
+
+/// Lint a guest file, detecting its format from the path
+///
+/// Returns `None` for files in an unrecognised format.
+pub fn lint_file(path: &str, content: &str) -> Option<(FileKind, Vec<LintIssue>)> {
+    let kind = FileKind::detect(path)?;
+    Some((kind, kind.lint(content)))
+}
+
+/// Errors in `after` that `before` did not have
+///
+/// Used to judge an edit without blaming it for problems the file already
+/// had. Line numbers shift with edits, so findings are compared by message.
+pub fn new_errors(kind: FileKind, before: &str, after: &str) -> Vec<LintIssue> {
+    let mut existing: Vec<String> = kind
+        .lint(before)
+        .into_iter()
+        .filter(LintIssue::is_error)
+        .map(|issue| issue.message)
+        .collect();
+
+    kind.lint(after)
+        .into_iter()
+        .filter(LintIssue::is_error)
+        .filter(
+            |issue| match existing.iter().position(|m| *m == issue.message) {
+                Some(pos) => {
+                    existing.remove(pos);
+                    false
+                }
+                None => true,
+            },
+        )
+        .collect()
+}
+
+/// Lines with comments and blank lines removed, numbered from 1
+fn content_lines(content: &str) -> impl Iterator<Item = (usize, &str)> {
+    content
+        .lines()
+        .enumerate()
+        .map(|(i, line)| (i + 1, line.trim()))
+        .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_detect() {
+        assert_eq!(FileKind::detect("/etc/fstab"), Some(FileKind::Fstab));
+        assert_eq!(
+            FileKind::detect("/etc/ssh/sshd_config"),
+            Some(FileKind::SshdConfig)
+        );
+        assert_eq!(
+            FileKind::detect("/etc/sudoers.d/90-cloud"),
+            Some(FileKind::Sudoers)
+        );
+        assert_eq!(FileKind::detect("/etc/sudoers.d/README.txt"), None);
+        assert_eq!(
+            FileKind::detect("/etc/nginx/sites-enabled/x.conf"),
+            Some(FileKind::Nginx)
+        );
+        assert_eq!(
+            FileKind::detect("/etc/systemd/system/app.service"),
+            Some(FileKind::SystemdUnit)
+        );
+        assert_eq!(
+            FileKind::detect("/etc/systemd/system/app.service.d/override.conf"),
+            Some(FileKind::SystemdDropIn)
+        );
+        assert_eq!(
+            FileKind::detect("/etc/crontab"),
+            Some(FileKind::SystemCrontab)
+        );
+        assert_eq!(
+            FileKind::detect("/var/spool/cron/crontabs/root"),
+            Some(FileKind::Crontab)
+        );
+        assert_eq!(
+            FileKind::detect("/etc/netplan/01.yaml"),
+            Some(FileKind::Yaml)
+        );
+        assert_eq!(FileKind::detect("/etc/hostname"), None);
+    }
+
+    #[test]
+    fn test_new_errors_ignores_existing() {
+        let before = "/dev/sda1 / ext4\n";
+        let after = "/dev/sda1 / ext4\n/dev/sdb1 data ext4 defaults 0 2\n";
+        assert!(new_errors(FileKind::Fstab, before, before).is_empty());
+
+        let introduced = new_errors(FileKind::Fstab, before, after);
+        assert_eq!(introduced.len(), 1);
+        assert_eq!(introduced[0].line, Some(2));
+    }
+}
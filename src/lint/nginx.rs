@@ -0,0 +1,165 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! nginx configuration checks
+//!
+//! Only the block structure is checked: directives end in `;`, blocks are
+//! named and balanced, and quotes are closed. That is what `nginx -t` most
+//! often trips over after a hand edit.
+
+use super::LintIssue;
+
+#[derive(Debug, PartialEq)]
+enum Token {
+    Word(String),
+    Semicolon,
+    Open,
+    Close,
+}
+
+/// Split the configuration into tokens with their line numbers
+fn tokenize(content: &str) -> Result<Vec<(usize, Token)>, LintIssue> {
+    let mut tokens = Vec::new();
+    let mut chars = content.chars().peekable();
+    let mut line = 1;
+
+    while let Some(c) = chars.next() {
+        match c {
+            '\n' => line += 1,
+            c if c.is_whitespace() => {}
+            '#' => while chars.next_if(|&c| c != '\n').is_some() {},
+            ';' => tokens.push((line, Token::Semicolon)),
+            '{' => tokens.push((line, Token::Open)),
+            '}' => tokens.push((line, Token::Close)),
+            '"' | '\'' => {
+                let start = line;
+                let mut word = String::new();
+                loop {
+                    match chars.next() {
+                        Some(ch) if ch == c => break,
+                        Some('\\') => {
+                            if let Some(escaped) = chars.next() {
+                                word.push(escaped);
+                            }
+                        }
+                        Some(ch) => {
+                            if ch == '\n' {
+                                line += 1;
+                            }
+                            word.push(ch);
+                        }
+                        None => return Err(LintIssue::error(start, "unterminated quoted string")),
+                    }
+                }
+                tokens.push((start, Token::Word(word)));
+            }
+            c => {
+                let mut word = String::from(c);
+                while let Some(&next) = chars.peek() {
+                    if next.is_whitespace() || matches!(next, ';' | '{' | '}') {
+                        // "${var}" is part of the word, not a block
+                        if next == '{' && word.ends_with('$') {
+                            word.extend(chars.by_ref().take_while(|&ch| ch != '}'));
+                            word.push('}');
+                            continue;
+                        }
+                        break;
+                    }
+                    word.push(next);
+                    chars.next();
+                }
+                tokens.push((line, Token::Word(word)));
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+pub fn lint(content: &str) -> Vec<LintIssue> {
+    let tokens = match tokenize(content) {
+        Ok(tokens) => tokens,
+        Err(issue) => return vec![issue],
+    };
+
+    let mut issues = Vec::new();
+    // Directive being read: (line, name)
+    let mut directive: Option<(usize, String)> = None;
+    // Open blocks: (line, name)
+    let mut blocks: Vec<(usize, String)> = Vec::new();
+
+    for (line, token) in tokens {
+        match token {
+            Token::Word(word) => {
+                directive.get_or_insert((line, word));
+            }
+            Token::Semicolon => {
+                if directive.take().is_none() {
+                    issues.push(LintIssue::error(line, "unexpected ';'"));
+                }
+            }
+            Token::Open => match directive.take() {
+                Some(block) => blocks.push(block),
+                None => issues.push(LintIssue::error(line, "block without a directive name")),
+            },
+            Token::Close => {
+                if let Some((start, name)) = directive.take() {
+                    issues.push(LintIssue::error(
+                        start,
+                        format!("directive '{}' is missing a ';'", name),
+                    ));
+                }
+                if blocks.pop().is_none() {
+                    issues.push(LintIssue::error(line, "unexpected '}'"));
+                }
+            }
+        }
+    }
+
+    if let Some((start, name)) = directive {
+        issues.push(LintIssue::error(
+            start,
+            format!("directive '{}' is missing a ';'", name),
+        ));
+    }
+    for (start, name) in blocks {
+        issues.push(LintIssue::error(
+            start,
+            format!("'{}' block is never closed", name),
+        ));
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_nginx() {
+        let content = r#"
+user www-data;
+http {
+    # comment with { brace
+    log_format main '$remote_addr "$request"';
+    server {
+        listen 80;
+        location / {
+            proxy_pass http://${upstream}:8080;
+        }
+    }
+}
+"#;
+        assert_eq!(lint(content), vec![]);
+    }
+
+    #[test]
+    fn test_broken_nginx() {
+        let content = "http {\n    server {\n        listen 80\n    }\n";
+        let issues = lint(content);
+        let lines: Vec<_> = issues.iter().map(|i| i.line.unwrap()).collect();
+        assert_eq!(lines, vec![3, 1]);
+
+        assert_eq!(lint("root '/srv;\n").len(), 1);
+        assert_eq!(lint("}\n")[0].message, "unexpected '}'");
+    }
+}
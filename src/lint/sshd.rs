@@ -0,0 +1,215 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! sshd_config checks
+
+use super::{content_lines, LintIssue};
+
+/// Keywords taking yes/no
+const BOOLEAN: &[&str] = &[
+    "challengeresponseauthentication",
+    "disableforwarding",
+    "exposeauthinfo",
+    "gatewayports",
+    "gssapiauthentication",
+    "gssapicleanupcredentials",
+    "hostbasedauthentication",
+    "ignorerhosts",
+    "ignoreuserknownhosts",
+    "kbdinteractiveauthentication",
+    "passwordauthentication",
+    "permitemptypasswords",
+    "permittty",
+    "permituserenvironment",
+    "permituserrc",
+    "printlastlog",
+    "printmotd",
+    "pubkeyauthentication",
+    "strictmodes",
+    "tcpkeepalive",
+    "usedns",
+    "usepam",
+    "x11forwarding",
+    "x11uselocalhost",
+];
+
+/// Keywords with a fixed set of values
+const ENUMERATED: &[(&str, &[&str])] = &[
+    (
+        "permitrootlogin",
+        &[
+            "yes",
+            "no",
+            "prohibit-password",
+            "without-password",
+            "forced-commands-only",
+        ],
+    ),
+    (
+        "allowtcpforwarding",
+        &["yes", "no", "all", "local", "remote"],
+    ),
+    (
+        "allowstreamlocalforwarding",
+        &["yes", "no", "all", "local", "remote"],
+    ),
+    ("allowagentforwarding", &["yes", "no"]),
+    ("compression", &["yes", "no", "delayed"]),
+    ("permittunnel", &["yes", "no", "point-to-point", "ethernet"]),
+    ("addressfamily", &["any", "inet", "inet6"]),
+    (
+        "loglevel",
+        &[
+            "quiet", "fatal", "error", "info", "verbose", "debug", "debug1", "debug2", "debug3",
+        ],
+    ),
+];
+
+/// Other keywords sshd accepts
+const OTHER: &[&str] = &[
+    "acceptenv",
+    "allowgroups",
+    "allowusers",
+    "authenticationmethods",
+    "authorizedkeyscommand",
+    "authorizedkeyscommanduser",
+    "authorizedkeysfile",
+    "authorizedprincipalscommand",
+    "authorizedprincipalscommanduser",
+    "authorizedprincipalsfile",
+    "banner",
+    "casignaturealgorithms",
+    "channeltimeout",
+    "chrootdirectory",
+    "ciphers",
+    "clientalivecountmax",
+    "clientaliveinterval",
+    "denygroups",
+    "denyusers",
+    "fingerprinthash",
+    "forcecommand",
+    "hostbasedacceptedalgorithms",
+    "hostcertificate",
+    "hostkey",
+    "hostkeyagent",
+    "hostkeyalgorithms",
+    "include",
+    "ipqos",
+    "kexalgorithms",
+    "listenaddress",
+    "logingracetime",
+    "logverbose",
+    "macs",
+    "match",
+    "maxauthtries",
+    "maxsessions",
+    "maxstartups",
+    "modulifile",
+    "persourcemaxstartups",
+    "persourcenetblocksize",
+    "permitlisten",
+    "permitopen",
+    "pidfile",
+    "port",
+    "pubkeyacceptedalgorithms",
+    "pubkeyacceptedkeytypes",
+    "pubkeyauthoptions",
+    "rekeylimit",
+    "requiredrsasize",
+    "revokedkeys",
+    "securitykeyprovider",
+    "setenv",
+    "streamlocalbindmask",
+    "streamlocalbindunlink",
+    "subsystem",
+    "syslogfacility",
+    "trustedusercakeys",
+    "unusedconnectiontimeout",
+    "versionaddendum",
+    "x11displayoffset",
+    "xauthlocation",
+];
+
+pub fn lint(content: &str) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+
+    for (line_no, line) in content_lines(content) {
+        // "Keyword value" or "Keyword=value"
+        let (keyword, value) = match line.find(|c: char| c.is_whitespace() || c == '=') {
+            Some(pos) => {
+                let rest = line[pos..].trim_start();
+                let rest = rest.strip_prefix('=').unwrap_or(rest).trim();
+                (&line[..pos], rest)
+            }
+            None => (line, ""),
+        };
+        let key = keyword.to_ascii_lowercase();
+
+        if value.is_empty() {
+            issues.push(LintIssue::error(
+                line_no,
+                format!("{} has no value", keyword),
+            ));
+            continue;
+        }
+        let first = value
+            .split_whitespace()
+            .next()
+            .unwrap_or(value)
+            .to_ascii_lowercase();
+
+        if BOOLEAN.contains(&key.as_str()) {
+            if first != "yes" && first != "no" {
+                issues.push(LintIssue::error(
+                    line_no,
+                    format!("{} must be yes or no, not '{}'", keyword, value),
+                ));
+            }
+        } else if let Some((_, allowed)) = ENUMERATED.iter().find(|(k, _)| *k == key) {
+            if !allowed.contains(&first.as_str()) {
+                issues.push(LintIssue::error(
+                    line_no,
+                    format!(
+                        "{} must be one of {}, not '{}'",
+                        keyword,
+                        allowed.join(", "),
+                        value
+                    ),
+                ));
+            }
+        } else if key == "port" {
+            if !matches!(value.parse::<u32>(), Ok(1..=65535)) {
+                issues.push(LintIssue::error(
+                    line_no,
+                    format!("invalid port '{}'", value),
+                ));
+            }
+        } else if !OTHER.contains(&key.as_str()) {
+            issues.push(LintIssue::warning(
+                line_no,
+                format!("unknown keyword {}", keyword),
+            ));
+        }
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_sshd_config() {
+        let content = "Port 22\n\
+            PermitRootLogin prohibit-password\n\
+            PasswordAuthentication=no\n\
+            Match User backup\n\
+            \tForceCommand internal-sftp\n";
+        assert!(lint(content).is_empty());
+
+        let content = "Port 70000\nPermitRootLogin maybe\nUsePAM\nPasswordAuthenticaton no\n";
+        let issues = lint(content);
+        assert_eq!(issues.len(), 4);
+        assert!(issues[..3].iter().all(LintIssue::is_error));
+        assert!(!issues[3].is_error());
+    }
+}
@@ -0,0 +1,84 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! YAML, JSON and TOML syntax checks
+
+use super::LintIssue;
+use serde::Deserialize;
+
+/// Parser message without the position, which is reported separately
+fn message(error: &impl ToString) -> String {
+    let message = error.to_string();
+    match message.find(" at line ") {
+        Some(pos) => message[..pos].to_string(),
+        None => message,
+    }
+}
+
+pub fn lint_json(content: &str) -> Vec<LintIssue> {
+    if content.trim().is_empty() {
+        return Vec::new();
+    }
+    match serde_json::from_str::<serde_json::Value>(content) {
+        Ok(_) => Vec::new(),
+        Err(e) => vec![LintIssue::error(e.line().max(1), message(&e))],
+    }
+}
+
+pub fn lint_yaml(content: &str) -> Vec<LintIssue> {
+    // Files may hold several documents separated by "---"
+    for document in serde_yaml::Deserializer::from_str(content) {
+        if let Err(e) = serde_yaml::Value::deserialize(document) {
+            let line = e.location().map(|l| l.line()).unwrap_or(1);
+            return vec![LintIssue::error(line, message(&e))];
+        }
+    }
+    Vec::new()
+}
+
+pub fn lint_toml(content: &str) -> Vec<LintIssue> {
+    match toml::from_str::<toml::Table>(content) {
+        Ok(_) => Vec::new(),
+        Err(e) => {
+            let offset = e.span().map(|span| span.start).unwrap_or(0);
+            let line = content
+                .get(..offset)
+                .unwrap_or(content)
+                .matches('\n')
+                .count()
+                + 1;
+            vec![LintIssue::error(line, e.message().trim())]
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_json() {
+        assert!(lint_json("{\"a\": [1, 2]}\n").is_empty());
+        assert!(lint_json("").is_empty());
+
+        let issues = lint_json("{\n  \"a\": 1,\n}\n");
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].line, Some(3));
+    }
+
+    #[test]
+    fn test_yaml() {
+        assert!(lint_yaml("a: 1\n---\nb: [1, 2]\n").is_empty());
+
+        let issues = lint_yaml("network:\n  ethernets:\n    eth0:\n   dhcp4: true\n  - x\n");
+        assert_eq!(issues.len(), 1);
+        assert!(issues[0].is_error());
+    }
+
+    #[test]
+    fn test_toml() {
+        assert!(lint_toml("[server]\nport = 8080\n").is_empty());
+
+        let issues = lint_toml("[server]\nport = \n");
+        assert_eq!(issues.len(), 1);
+        assert_eq!(issues[0].line, Some(2));
+    }
+}
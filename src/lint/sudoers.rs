@@ -0,0 +1,226 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! sudoers checks
+//!
+//! A syntax error in any sudoers file makes sudo refuse to run at all, so
+//! these checks err on the side of reporting errors.
+
+use super::LintIssue;
+
+const ALIAS_KINDS: &[&str] = &[
+    "User_Alias",
+    "Runas_Alias",
+    "Host_Alias",
+    "Cmnd_Alias",
+    "Cmd_Alias",
+];
+
+/// Tags that may precede a command
+const TAGS: &[&str] = &[
+    "NOPASSWD",
+    "PASSWD",
+    "NOEXEC",
+    "EXEC",
+    "SETENV",
+    "NOSETENV",
+    "LOG_INPUT",
+    "NOLOG_INPUT",
+    "LOG_OUTPUT",
+    "NOLOG_OUTPUT",
+    "MAIL",
+    "NOMAIL",
+    "FOLLOW",
+    "NOFOLLOW",
+    "INTERCEPT",
+    "NOINTERCEPT",
+];
+
+/// Logical lines (continuations joined), numbered by their first line
+fn logical_lines(content: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+
+    for (i, raw) in content.lines().enumerate() {
+        let (continued, text) = match raw.strip_suffix('\\') {
+            Some(text) => (true, text),
+            None => (false, raw),
+        };
+        let entry = current.get_or_insert_with(|| (i + 1, String::new()));
+        entry.1.push_str(text);
+        entry.1.push(' ');
+        if !continued {
+            lines.extend(current.take());
+        }
+    }
+    lines.extend(current);
+    lines
+}
+
+fn is_alias_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars.next().is_some_and(|c| c.is_ascii_uppercase())
+        && chars.all(|c| c.is_ascii_uppercase() || c.is_ascii_digit() || c == '_')
+}
+
+/// Check the command list of a user specification
+fn check_commands(line_no: usize, commands: &str, issues: &mut Vec<LintIssue>) {
+    for spec in commands.split(',') {
+        let mut spec = spec.trim();
+
+        // Optional (runas) list
+        if let Some(rest) = spec.strip_prefix('(') {
+            match rest.find(')') {
+                Some(end) => spec = rest[end + 1..].trim_start(),
+                None => {
+                    issues.push(LintIssue::error(line_no, "unclosed '(' in runas list"));
+                    return;
+                }
+            }
+        }
+
+        // Tags such as NOPASSWD:
+        while let Some((tag, rest)) = spec.split_once(':') {
+            if !TAGS.contains(&tag.trim()) {
+                break;
+            }
+            spec = rest.trim_start();
+        }
+
+        let command = spec
+            .trim_start_matches('!')
+            .split_whitespace()
+            .next()
+            .unwrap_or("");
+        if command.is_empty() {
+            issues.push(LintIssue::error(
+                line_no,
+                "empty command in user specification",
+            ));
+        } else if !(command.starts_with('/')
+            || command == "ALL"
+            || command == "sudoedit"
+            || is_alias_name(command))
+        {
+            issues.push(LintIssue::error(
+                line_no,
+                format!("command '{}' must be a fully qualified path", command),
+            ));
+        }
+    }
+}
+
+pub fn lint(content: &str) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+
+    for (line_no, line) in logical_lines(content) {
+        let line = line.trim();
+        if line.is_empty() {
+            continue;
+        }
+
+        // Include directives look like comments
+        for directive in ["#include", "#includedir", "@include", "@includedir"] {
+            if let Some(rest) = line.strip_prefix(directive) {
+                if rest.starts_with(char::is_whitespace) || rest.is_empty() {
+                    if rest.trim().is_empty() {
+                        issues.push(LintIssue::error(
+                            line_no,
+                            format!("{} without a path", directive),
+                        ));
+                    }
+                    break;
+                }
+            }
+        }
+        if line.starts_with('#') || line.starts_with('@') {
+            continue;
+        }
+
+        let first = line.split_whitespace().next().unwrap_or("");
+
+        if first.starts_with("Defaults") {
+            if line[first.len()..].trim().is_empty() {
+                issues.push(LintIssue::error(line_no, "Defaults without settings"));
+            }
+            continue;
+        }
+
+        if ALIAS_KINDS.contains(&first) {
+            let rest = line[first.len()..].trim();
+            // Several aliases may be joined with ':'
+            for definition in rest.split(':') {
+                match definition.split_once('=') {
+                    Some((name, members)) => {
+                        let name = name.trim();
+                        if !is_alias_name(name) {
+                            issues.push(LintIssue::error(
+                                line_no,
+                                format!("alias name '{}' must be upper case", name),
+                            ));
+                        }
+                        if members.trim().is_empty() {
+                            issues.push(LintIssue::error(
+                                line_no,
+                                format!("alias {} is empty", name),
+                            ));
+                        }
+                    }
+                    None => issues.push(LintIssue::error(
+                        line_no,
+                        format!("expected '{} NAME = ...'", first),
+                    )),
+                }
+            }
+            continue;
+        }
+
+        // User specification: users hosts = [(runas)] [TAGS:] commands
+        let Some((who, commands)) = line.split_once('=') else {
+            issues.push(LintIssue::error(
+                line_no,
+                "expected 'user host = command' user specification",
+            ));
+            continue;
+        };
+        if who.split_whitespace().count() < 2 {
+            issues.push(LintIssue::error(
+                line_no,
+                "user specification needs a user and a host before '='",
+            ));
+        }
+        if line.matches('(').count() != line.matches(')').count() {
+            issues.push(LintIssue::error(line_no, "unbalanced parentheses"));
+            continue;
+        }
+        check_commands(line_no, commands, &mut issues);
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_sudoers() {
+        let content = "Defaults env_reset\n\
+            Defaults:%wheel !lecture\n\
+            Cmnd_Alias SERVICES = /usr/bin/systemctl, \\\n    /usr/sbin/service\n\
+            root ALL=(ALL:ALL) ALL\n\
+            %sudo ALL=(ALL) NOPASSWD: SERVICES, /usr/bin/apt update\n\
+            #includedir /etc/sudoers.d\n\
+            @include /etc/sudoers.local\n";
+        assert_eq!(lint(content), vec![]);
+    }
+
+    #[test]
+    fn test_broken_sudoers() {
+        let content = "Defaults\n\
+            deploy ALL=(ALL) NOPASSWD: systemctl restart app\n\
+            admin ALL\n\
+            User_Alias admins = alice\n\
+            ops ALL=(root ALL\n";
+        let lines: Vec<_> = lint(content).iter().map(|i| i.line.unwrap()).collect();
+        assert_eq!(lines, vec![1, 2, 3, 4, 5]);
+    }
+}
@@ -0,0 +1,237 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! systemd unit and drop-in checks
+
+use super::{LintIssue, UNIT_SUFFIXES};
+
+const SECTIONS: &[&str] = &[
+    "Unit",
+    "Install",
+    "Service",
+    "Socket",
+    "Timer",
+    "Mount",
+    "Automount",
+    "Swap",
+    "Path",
+    "Slice",
+    "Scope",
+];
+
+const SERVICE_TYPES: &[&str] = &[
+    "simple",
+    "exec",
+    "forking",
+    "oneshot",
+    "dbus",
+    "notify",
+    "notify-reload",
+    "idle",
+];
+
+const RESTART_VALUES: &[&str] = &[
+    "no",
+    "always",
+    "on-success",
+    "on-failure",
+    "on-abnormal",
+    "on-abort",
+    "on-watchdog",
+];
+
+/// Keys holding command lines
+const EXEC_KEYS: &[&str] = &[
+    "ExecStart",
+    "ExecStartPre",
+    "ExecStartPost",
+    "ExecReload",
+    "ExecStop",
+    "ExecStopPost",
+    "ExecCondition",
+];
+
+/// Logical lines (continuations joined), numbered by their first line
+fn logical_lines(content: &str) -> Vec<(usize, String)> {
+    let mut lines = Vec::new();
+    let mut current: Option<(usize, String)> = None;
+
+    for (i, raw) in content.lines().enumerate() {
+        let trimmed = raw.trim();
+        // Comments inside a continuation are skipped, not ended on
+        if trimmed.starts_with('#') || trimmed.starts_with(';') {
+            continue;
+        }
+        let (continued, text) = match trimmed.strip_suffix('\\') {
+            Some(text) => (true, text),
+            None => (false, trimmed),
+        };
+        let entry = current.get_or_insert_with(|| (i + 1, String::new()));
+        if !entry.1.is_empty() {
+            entry.1.push(' ');
+        }
+        entry.1.push_str(text.trim());
+        if !continued {
+            lines.extend(current.take());
+        }
+    }
+    lines.extend(current);
+    lines
+}
+
+pub fn lint(content: &str, dropin: bool) -> Vec<LintIssue> {
+    let mut issues = Vec::new();
+    let mut section: Option<String> = None;
+    // Line of the [Service] header, ExecStart seen, Type=
+    let mut service: Option<usize> = None;
+    let mut has_exec_start = false;
+    let mut service_type = String::new();
+
+    for (line_no, line) in logical_lines(content) {
+        if line.is_empty() {
+            continue;
+        }
+
+        if line.starts_with('[') {
+            match line.strip_prefix('[').and_then(|l| l.strip_suffix(']')) {
+                Some(name) if !name.is_empty() && !name.contains(['[', ']']) => {
+                    if !SECTIONS.contains(&name) && !name.starts_with("X-") {
+                        issues.push(LintIssue::warning(
+                            line_no,
+                            format!("unknown section [{}]", name),
+                        ));
+                    }
+                    if name == "Service" {
+                        service = Some(line_no);
+                    }
+                    section = Some(name.to_string());
+                }
+                _ => issues.push(LintIssue::error(
+                    line_no,
+                    format!("malformed section header '{}'", line),
+                )),
+            }
+            continue;
+        }
+
+        let Some((key, value)) = line.split_once('=') else {
+            issues.push(LintIssue::error(
+                line_no,
+                format!("expected 'Key=value', found '{}'", line),
+            ));
+            continue;
+        };
+        let (key, value) = (key.trim(), value.trim());
+        if key.is_empty() {
+            issues.push(LintIssue::error(line_no, "assignment without a key"));
+            continue;
+        }
+        let Some(section) = section.as_deref() else {
+            issues.push(LintIssue::error(
+                line_no,
+                format!("{} is set outside of any section", key),
+            ));
+            continue;
+        };
+
+        match (section, key) {
+            ("Service", "Type") => {
+                if !value.is_empty() && !SERVICE_TYPES.contains(&value) {
+                    issues.push(LintIssue::error(line_no, format!("invalid Type={}", value)));
+                }
+                service_type = value.to_string();
+            }
+            ("Service", "Restart") if !value.is_empty() && !RESTART_VALUES.contains(&value) => {
+                issues.push(LintIssue::error(
+                    line_no,
+                    format!("invalid Restart={}", value),
+                ));
+            }
+            ("Service", key) if EXEC_KEYS.contains(&key) => {
+                if key == "ExecStart" {
+                    // An empty assignment resets the list
+                    has_exec_start = !value.is_empty();
+                }
+                let command = value.trim_start_matches(['@', '-', ':', '+', '!']);
+                if !command.is_empty() && !command.starts_with('/') {
+                    issues.push(LintIssue::warning(
+                        line_no,
+                        format!(
+                            "{} command '{}' is not an absolute path (requires systemd 239 or later)",
+                            key,
+                            command.split_whitespace().next().unwrap_or(command)
+                        ),
+                    ));
+                }
+            }
+            ("Install", "WantedBy" | "RequiredBy" | "UpheldBy") => {
+                for unit in value.split_whitespace() {
+                    let suffix = unit.rsplit_once('.').map(|(_, s)| s).unwrap_or("");
+                    if !UNIT_SUFFIXES.contains(&suffix) {
+                        issues.push(LintIssue::warning(
+                            line_no,
+                            format!("{}={} is not a unit name", key, unit),
+                        ));
+                    }
+                }
+            }
+            _ => {}
+        }
+    }
+
+    if let Some(line_no) = service {
+        if !dropin && !has_exec_start && service_type != "oneshot" {
+            issues.push(LintIssue::error(
+                line_no,
+                "service has no ExecStart= and is not Type=oneshot",
+            ));
+        }
+    }
+
+    issues
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_valid_unit() {
+        let content = "[Unit]\n\
+            Description=App\n\
+            \n\
+            [Service]\n\
+            Type=notify\n\
+            ExecStart=/usr/bin/app \\\n  --config /etc/app.conf\n\
+            Restart=on-failure\n\
+            \n\
+            [Install]\n\
+            WantedBy=multi-user.target\n";
+        assert_eq!(lint(content, false), vec![]);
+
+        // Drop-ins only override some settings
+        assert_eq!(
+            lint("[Service]\nExecStart=\nExecStart=/usr/bin/app -v\n", true),
+            vec![]
+        );
+        assert_eq!(lint("[Service]\nEnvironment=DEBUG=1\n", true), vec![]);
+    }
+
+    #[test]
+    fn test_broken_unit() {
+        let content = "Description=App\n\
+            [Service\n\
+            [Service]\n\
+            Type=daemon\n\
+            Restart=sometimes\n\
+            ExecStartPre=mkdir /run/app\n\
+            [Install]\n\
+            WantedBy=multi-user\n";
+        let issues = lint(content, false);
+        let errors: Vec<_> = issues
+            .iter()
+            .filter(|i| i.is_error())
+            .map(|i| i.line.unwrap())
+            .collect();
+        assert_eq!(errors, vec![1, 2, 4, 5, 3]);
+        assert_eq!(issues.len(), 7);
+    }
+}
@@ -2,22 +2,46 @@
 //! guestctl CLI - Guest VM toolkit
 
 use anyhow::Context;
-use clap::{CommandFactory, Parser, Subcommand};
+use clap::{CommandFactory, FromArgMatches, Parser, Subcommand};
 use clap_complete::{generate, shells};
 use colored::Colorize;
-use guestkit::{converters::DiskConverter, VERSION};
+use guestkit::disk::VmdkImage;
+use guestkit::converters::{copy, CopyOptions, DiskConverter, IoPriority};
+use guestkit::{DiskFormat, VERSION};
+use std::ffi::OsString;
 use std::io;
 use std::path::PathBuf;
 
 mod cli;
 use cli::commands::*;
+use cli::align::AlignCheckCommand;
+use cli::audit_log::{audited, AuditLogCommand};
+use cli::bitmap::BitmapCommand;
+use cli::chain::{ChainCommand, RebaseCommand};
+use cli::catalog::{parse_image_ref, CatalogCommand};
+use cli::convert_boot::ConvertBootCommand;
+use cli::customize::CustomizeCommand;
+use cli::generate::GenerateCommand;
+use cli::share::ShareCommand;
+use cli::sync::SyncCommand;
+use cli::import_ova::ImportOvaCommand;
+#[cfg(feature = "oci")]
+use cli::oci::OciCommand;
+#[cfg(feature = "publish")]
+use cli::publish::parse_publish_target;
+use cli::keys::{parse_key_spec, KeySpec};
+use cli::notify::{parse_notify_target, NotifyTarget};
+use cli::plain;
 use cli::plan::PlanCommand;
+use cli::replay::ReplayCommand;
+use cli::vulndb::VulndbCommand;
 
 /// guestctl - Guest VM toolkit for disk inspection and manipulation
 #[derive(Parser)]
 #[command(name = "guestctl")]
 #[command(version = VERSION)]
 #[command(about = "Guest VM toolkit for disk inspection and manipulation", long_about = None)]
+#[command(allow_external_subcommands = true)]
 struct Cli {
     /// Verbose output
     #[arg(short, long, global = true)]
@@ -59,6 +83,52 @@ struct Cli {
     #[arg(long, global = true)]
     machine_readable: bool,
 
+    /// Plain ASCII output without emoji or box drawing, for screen readers (implies --no-color)
+    #[arg(long, global = true)]
+    plain: bool,
+
+    /// Message language (en, es, de); defaults to GUESTCTL_LANG or LANG
+    #[arg(long, global = true, value_name = "LANG")]
+    lang: Option<String>,
+
+    /// Key for an encrypted volume: SELECTOR:key:PASSPHRASE, SELECTOR:file:PATH,
+    /// SELECTOR:clevis or SELECTOR:prompt, where SELECTOR is a device, LUKS UUID
+    /// or "all" (repeatable)
+    #[arg(long = "key", global = true, value_name = "SELECTOR:TYPE[:VALUE]", value_parser = parse_key_spec)]
+    keys: Vec<KeySpec>,
+
+    /// OS to work on when an image has several (multi-boot, rescue
+    /// partitions): a root device as listed by inspect, or its number
+    #[arg(long, global = true, value_name = "ROOT")]
+    os_root: Option<String>,
+
+    /// Further disk of the VM, attached after the image for fstab mounts,
+    /// LVM and RAID spanning disks (repeatable)
+    #[arg(long = "disk", global = true, value_name = "PATH", value_parser = parse_image_ref)]
+    extra_disks: Vec<PathBuf>,
+
+    /// Mount options used instead of each fstab entry's when mounting the
+    /// guest's filesystems (e.g. "defaults" to also mount noauto entries,
+    /// "nouuid" for cloned XFS)
+    #[arg(long, global = true, value_name = "OPTS")]
+    mount_options: Option<String>,
+
+    /// Report when the command finishes: "desktop" or a webhook URL the JSON
+    /// summary is POSTed to (repeatable; default: GUESTCTL_NOTIFY)
+    #[arg(long, global = true, value_name = "TARGET", value_parser = parse_notify_target)]
+    notify: Vec<NotifyTarget>,
+
+    /// Only notify for commands running at least this long (default:
+    /// GUESTCTL_NOTIFY_AFTER or 0)
+    #[arg(long, global = true, value_name = "SECONDS")]
+    notify_after: Option<u64>,
+
+    /// Record the inspection calls of the command to FILE, for 'guestctl
+    /// replay' (holds call results such as OS details and package lists,
+    /// no file contents)
+    #[arg(long, global = true, value_name = "FILE")]
+    record_trace: Option<PathBuf>,
+
     #[command(subcommand)]
     command: Commands,
 }
@@ -68,8 +138,13 @@ enum Commands {
     /// Inspect a disk image and display OS information
     Inspect {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
+        /// Further disks of the guest, e.g. the other members of a software RAID
+        #[arg(value_parser = parse_image_ref)]
+        disks: Vec<PathBuf>,
+
         /// Output format (text, json, yaml, csv)
         #[arg(short, long, value_name = "FORMAT")]
         output: Option<String>,
@@ -122,9 +197,11 @@ enum Commands {
     /// Diff two disk images to show configuration changes
     Diff {
         /// First disk image
+        #[arg(value_parser = parse_image_ref)]
         image1: PathBuf,
 
         /// Second disk image
+        #[arg(value_parser = parse_image_ref)]
         image2: PathBuf,
 
         /// Output format (text, json, yaml)
@@ -135,10 +212,11 @@ enum Commands {
     /// Compare multiple VMs against a baseline
     Compare {
         /// Baseline disk image
+        #[arg(value_parser = parse_image_ref)]
         baseline: PathBuf,
 
         /// Disk images to compare
-        #[arg(required = true)]
+        #[arg(required = true, value_parser = parse_image_ref)]
         images: Vec<PathBuf>,
     },
 
@@ -146,6 +224,7 @@ enum Commands {
     #[command(alias = "ls")]
     List {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Path to list (default: /)
@@ -176,7 +255,7 @@ enum Commands {
         #[arg(long)]
         reverse: bool,
 
-        /// Filter by file pattern (glob)
+        /// Filter by file pattern (glob, e.g. "*.{conf,cfg}")
         #[arg(short = 'f', long)]
         filter: Option<String>,
 
@@ -187,12 +266,25 @@ enum Commands {
         /// Limit number of results
         #[arg(short = 'n', long)]
         limit: Option<usize>,
+
+        /// Exclude paths matching gitignore-style patterns (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        exclude: Vec<String>,
+
+        /// Read exclude patterns from a gitignore-style file
+        #[arg(long)]
+        exclude_from: Option<PathBuf>,
+
+        /// Case-insensitive filter and exclude matching
+        #[arg(short = 'i', long)]
+        ignore_case: bool,
     },
 
     /// Extract a file from disk image
     #[command(alias = "get")]
     Extract {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Path in guest filesystem
@@ -202,7 +294,7 @@ enum Commands {
         #[arg(short, long)]
         output: PathBuf,
 
-        /// Preserve file permissions and timestamps
+        /// Preserve permissions, timestamps, xattrs, ACLs, SELinux labels and capabilities
         #[arg(short, long)]
         preserve: bool,
 
@@ -227,6 +319,7 @@ enum Commands {
     #[command(alias = "exec")]
     Execute {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Command and arguments to execute
@@ -237,15 +330,37 @@ enum Commands {
     /// Backup files from guest to tar archive
     Backup {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Path to backup in guest
         #[arg(default_value = "/")]
         path: String,
 
-        /// Output tar.gz file
+        /// Output archive file
         #[arg(short, long)]
         output: PathBuf,
+
+        /// Archive format (tar, tar.gz, tar.zst, cpio, zip); defaults to the
+        /// output extension, else tar.gz
+        #[arg(short, long)]
+        format: Option<String>,
+
+        /// Only archive paths matching these globs (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        include: Vec<String>,
+
+        /// Leave out paths matching these globs (comma-separated)
+        #[arg(short, long, value_delimiter = ',')]
+        exclude: Vec<String>,
+
+        /// Clamp modification times to this Unix time for reproducible archives
+        #[arg(long)]
+        mtime: Option<i64>,
+
+        /// Compression level (gzip and zip 0-9, zstd 1-22)
+        #[arg(short, long)]
+        level: Option<i32>,
     },
 
     /// Convert disk image format
@@ -261,7 +376,7 @@ enum Commands {
         #[arg(short, long, default_value = "qcow2")]
         format: String,
 
-        /// Enable compression (qcow2 only)
+        /// Enable compression (qcow2; vmdk output becomes streamOptimized)
         #[arg(short, long)]
         compress: bool,
 
@@ -273,10 +388,24 @@ enum Commands {
         #[arg(short = 'P', long)]
         progress: bool,
 
-        /// Verify conversion with checksum
+        /// Compare the output with a SHA-256 manifest of the source, block
+        /// by block; exits 1 when they differ
         #[arg(long)]
         verify: bool,
 
+        /// Write the source's block manifest to FILE (JSON)
+        #[arg(long, value_name = "FILE", requires = "verify")]
+        manifest: Option<PathBuf>,
+
+        /// Write the verification report to FILE (JSON)
+        #[arg(long, value_name = "FILE", requires = "verify")]
+        verify_report: Option<PathBuf>,
+
+        /// Journal the copy to OUTPUT.journal and continue an interrupted
+        /// conversion from it (raw output only)
+        #[arg(long)]
+        resume: bool,
+
         /// Sparse output (don't write zeros)
         #[arg(short = 'S', long)]
         sparse: bool,
@@ -292,6 +421,27 @@ enum Commands {
         /// Buffer size in MB for I/O operations
         #[arg(long, value_name = "SIZE", default_value = "4")]
         buffer_size: usize,
+
+        /// io_uring requests in flight when writing raw output (-j sets the
+        /// copy threads and qemu-img coroutines)
+        #[arg(long, value_name = "N", default_value = "32")]
+        queue_depth: usize,
+
+        /// Read at most this many MB (10^6 bytes) per second, so guests on
+        /// the same storage keep their I/O
+        #[arg(long, value_name = "MB/s", value_parser = clap::value_parser!(u64).range(1..))]
+        bwlimit: Option<u64>,
+
+        /// I/O scheduling class: idle, best-effort or best-effort:0-7 (as
+        /// with ionice; needs the BFQ scheduler)
+        #[arg(long, value_name = "CLASS")]
+        ionice: Option<IoPriority>,
+
+        /// Upload the output to glance://NAME or proxmox://HOST/NODE/STORAGE
+        /// (repeatable)
+        #[cfg(feature = "publish")]
+        #[arg(long, value_name = "URI", value_parser = parse_publish_target)]
+        publish: Vec<guestkit::publish::PublishTarget>,
     },
 
     /// Create a new disk image
@@ -312,6 +462,7 @@ enum Commands {
     #[command(alias = "fsck")]
     Check {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Specific device to check (optional)
@@ -323,28 +474,43 @@ enum Commands {
     #[command(alias = "df")]
     Usage {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
     },
 
     /// Detect disk image format
     Detect {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
     },
 
     /// Get disk image information
     Info {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
     },
 
     /// Inspect multiple disk images in batch
     #[command(name = "inspect-batch")]
     InspectBatch {
-        /// Disk image paths (can use glob patterns)
-        #[arg(required = true)]
+        /// Disk image paths or catalog references (can use glob patterns)
+        #[arg(required_unless_present_any = ["tags", "resume"], value_parser = parse_image_ref)]
         images: Vec<PathBuf>,
 
+        /// Also inspect cataloged images carrying this tag (repeatable, all must match)
+        #[arg(short, long = "tag", value_name = "TAG")]
+        tags: Vec<String>,
+
+        /// Progress journal to write (default: a new one in the user data directory)
+        #[arg(long, value_name = "FILE", conflicts_with = "resume")]
+        journal: Option<PathBuf>,
+
+        /// Continue an interrupted run from its journal, skipping finished images
+        #[arg(long, value_name = "JOURNAL", conflicts_with_all = ["images", "tags"])]
+        resume: Option<PathBuf>,
+
         /// Number of parallel workers (default: 4)
         #[arg(short, long, default_value = "4")]
         parallel: usize,
@@ -370,17 +536,23 @@ enum Commands {
     #[command(alias = "fs")]
     Filesystems {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Show detailed information
         #[arg(short, long)]
         detailed: bool,
+
+        /// List btrfs subvolumes and snapshots with their sizes
+        #[arg(long)]
+        subvolumes: bool,
     },
 
     /// List installed packages
     #[command(alias = "pkg")]
     Packages {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Filter packages by name
@@ -399,6 +571,7 @@ enum Commands {
     /// Read file content from disk image
     Cat {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Path to file in guest filesystem
@@ -411,12 +584,17 @@ enum Commands {
         /// Show non-printing characters
         #[arg(short = 'A', long)]
         show_all: bool,
+
+        /// Print at most this many bytes as head and tail (e.g. 512K, 10M; 0 = no limit)
+        #[arg(long, value_name = "SIZE", default_value = cli::preview::DEFAULT_MAX_BYTES, value_parser = cli::output::parse_size)]
+        max_bytes: u64,
     },
 
     /// Search for files by name or pattern
     #[command(alias = "find")]
     Search {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Search pattern (glob or regex)
@@ -449,11 +627,20 @@ enum Commands {
         /// Limit number of results
         #[arg(short = 'l', long)]
         limit: Option<usize>,
+
+        /// Exclude paths matching gitignore-style patterns (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        exclude: Vec<String>,
+
+        /// Read exclude patterns from a gitignore-style file
+        #[arg(long)]
+        exclude_from: Option<PathBuf>,
     },
 
     /// Search file contents (like grep)
     Grep {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Search pattern
@@ -494,11 +681,16 @@ enum Commands {
         /// Maximum results
         #[arg(short = 'm', long)]
         max_count: Option<usize>,
+
+        /// Search at most this many bytes of each file (e.g. 64M; 0 = no limit)
+        #[arg(long, value_name = "SIZE", default_value = "0", value_parser = cli::output::parse_size)]
+        max_bytes: u64,
     },
 
     /// Calculate file checksums
     Hash {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Path to file in guest filesystem
@@ -520,6 +712,7 @@ enum Commands {
     /// Security vulnerability scan
     Scan {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Scan type (packages, config, permissions, all)
@@ -538,14 +731,21 @@ enum Commands {
         #[arg(short = 'r', long)]
         report: bool,
 
-        /// Check CVE database for vulnerabilities
+        /// Check installed packages against the offline vulnerability
+        /// database (see `vulndb update`)
         #[arg(long)]
         check_cve: bool,
+
+        /// OpenVEX document; CVEs it marks not_affected or fixed are not
+        /// reported (repeatable)
+        #[arg(long, value_name = "FILE")]
+        vex: Vec<PathBuf>,
     },
 
     /// Benchmark disk I/O performance
     Benchmark {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Test type (read, write, random, sequential)
@@ -565,20 +765,21 @@ enum Commands {
         iterations: usize,
     },
 
-    /// Manage disk snapshots
+    /// Manage qcow2 internal snapshots, without qemu-img
     Snapshot {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
-        /// Snapshot operation (create, list, delete, revert)
+        /// Snapshot operation (create, list, delete, revert, info)
         #[arg(value_enum)]
         operation: SnapshotOperation,
 
-        /// Snapshot name
+        /// Snapshot name, or ID for delete, revert and info
         #[arg(short = 'n', long)]
         name: Option<String>,
 
-        /// Snapshot description
+        /// Snapshot description, for the audit log (qcow2 doesn't store one)
         #[arg(long)]
         description: Option<String>,
     },
@@ -586,9 +787,11 @@ enum Commands {
     /// Compare specific files between disk images
     DiffFiles {
         /// First disk image
+        #[arg(value_parser = parse_image_ref)]
         image1: PathBuf,
 
         /// Second disk image
+        #[arg(value_parser = parse_image_ref)]
         image2: PathBuf,
 
         /// Path to compare
@@ -611,6 +814,7 @@ enum Commands {
     /// Find large files in disk image
     FindLarge {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Starting path
@@ -644,7 +848,7 @@ enum Commands {
         /// Destination file path
         dest_path: String,
 
-        /// Preserve permissions and timestamps
+        /// Preserve permissions, ownership, xattrs, ACLs, SELinux labels and capabilities
         #[arg(short = 'p', long)]
         preserve: bool,
 
@@ -656,6 +860,7 @@ enum Commands {
     /// Find duplicate files
     FindDuplicates {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Starting path
@@ -674,6 +879,7 @@ enum Commands {
     /// Analyze disk usage by directory
     DiskUsage {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Starting path
@@ -696,6 +902,7 @@ enum Commands {
     /// Build forensic timeline from multiple sources
     Timeline {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Start time filter (ISO 8601)
@@ -706,18 +913,27 @@ enum Commands {
         #[arg(long)]
         end_time: Option<String>,
 
-        /// Data sources (files, packages, logs)
+        /// Data sources (files, packages, logs, logins)
         #[arg(short = 's', long, value_delimiter = ',')]
         sources: Vec<String>,
 
-        /// Output format (text, json, csv)
+        /// Only paths below this directory (repeatable; bodyfile and l2tcsv)
+        #[arg(short = 'p', long, value_name = "PATH")]
+        path: Vec<String>,
+
+        /// Output format (text, json, csv, bodyfile, l2tcsv)
         #[arg(short = 'f', long, default_value = "text")]
         format: String,
+
+        /// Output file (stdout if not specified; bodyfile and l2tcsv)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
     },
 
     /// Create unique fingerprint for disk image
     Fingerprint {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Hash algorithm
@@ -736,9 +952,11 @@ enum Commands {
     /// Detect configuration drift from baseline
     Drift {
         /// Baseline disk image
+        #[arg(value_parser = parse_image_ref)]
         baseline: PathBuf,
 
         /// Current disk image to compare
+        #[arg(value_parser = parse_image_ref)]
         current: PathBuf,
 
         /// Paths to ignore (comma-separated)
@@ -757,6 +975,7 @@ enum Commands {
     /// AI-powered deep analysis with insights
     Analyze {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Analysis focus areas (security, performance, compliance, maintainability)
@@ -775,6 +994,7 @@ enum Commands {
     /// Scan for exposed secrets and credentials
     Secrets {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Paths to scan (comma-separated)
@@ -785,10 +1005,38 @@ enum Commands {
         #[arg(short = 'p', long, value_delimiter = ',')]
         patterns: Vec<String>,
 
-        /// Paths to exclude (comma-separated)
+        /// Only scan paths matching gitignore-style patterns (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        include: Vec<String>,
+
+        /// Exclude paths matching gitignore-style patterns (comma-separated)
         #[arg(short = 'e', long, value_delimiter = ',')]
         exclude: Vec<String>,
 
+        /// Read exclude patterns from a gitignore-style file
+        #[arg(long)]
+        exclude_from: Option<PathBuf>,
+
+        /// Case-insensitive include and exclude matching
+        #[arg(short = 'i', long)]
+        ignore_case: bool,
+
+        /// Extra TOML rule pack (repeatable)
+        #[arg(long = "rules", value_name = "FILE")]
+        rule_packs: Vec<PathBuf>,
+
+        /// TOML allowlist of paths, secret regexes and rule IDs (repeatable)
+        #[arg(long = "allowlist", value_name = "FILE")]
+        allowlists: Vec<PathBuf>,
+
+        /// Leave out the findings recorded in this baseline
+        #[arg(long, value_name = "FILE")]
+        baseline: Option<PathBuf>,
+
+        /// Record every finding in a baseline file
+        #[arg(long, value_name = "FILE")]
+        write_baseline: Option<PathBuf>,
+
         /// Show actual secret content (WARNING: sensitive)
         #[arg(long)]
         show_content: bool,
@@ -801,6 +1049,7 @@ enum Commands {
     /// Automated rescue and recovery operations
     Rescue {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Rescue operation (reset-password, fix-fstab, fix-grub, enable-ssh)
@@ -827,6 +1076,7 @@ enum Commands {
     /// Optimize disk image (cleanup, compact)
     Optimize {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Operations to perform (temp, logs, cache, packages)
@@ -840,11 +1090,20 @@ enum Commands {
         /// Dry run (show what would be removed)
         #[arg(long)]
         dry_run: bool,
+
+        /// Never remove paths matching gitignore-style patterns (comma-separated)
+        #[arg(long, value_delimiter = ',')]
+        exclude: Vec<String>,
+
+        /// Read exclude patterns from a gitignore-style file
+        #[arg(long)]
+        exclude_from: Option<PathBuf>,
     },
 
     /// Analyze network configuration
     Network {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Show routing information
@@ -867,6 +1126,7 @@ enum Commands {
     /// Compliance checking against security standards
     Compliance {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Security standard (cis, pci-dss, hipaa)
@@ -889,6 +1149,7 @@ enum Commands {
     /// Malware and rootkit detection
     Malware {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Deep scan (more thorough but slower)
@@ -899,21 +1160,37 @@ enum Commands {
         #[arg(long)]
         check_rootkits: bool,
 
-        /// YARA rules file
+        /// YARA rules file, or a directory of .yar files (needs the
+        /// `yara` feature, which release builds enable)
         #[arg(long)]
         yara_rules: Option<PathBuf>,
 
+        /// Guest paths to scan with YARA (comma-separated; default: temp
+        /// directories, home directories, /opt and /usr/local, or / with
+        /// --deep-scan)
+        #[arg(long, value_delimiter = ',', requires = "yara_rules")]
+        yara_paths: Vec<String>,
+
         /// Quarantine suspicious files
         #[arg(short = 'q', long)]
         quarantine: bool,
+
+        /// Output format (text, json, sarif)
+        #[arg(long, default_value = "text")]
+        format: String,
+
+        /// Write the findings as an ATT&CK Navigator layer
+        #[arg(long)]
+        attack_layer: Option<PathBuf>,
     },
 
     /// System health and diagnostics
     Health {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
-        /// Specific checks to run (disk, services, security, packages, logs)
+        /// Specific checks to run (disk, services, security, packages, logs, crashes)
         #[arg(short = 'c', long, value_delimiter = ',')]
         checks: Vec<String>,
 
@@ -938,11 +1215,25 @@ enum Commands {
         #[arg(short = 's', long)]
         sysprep: bool,
 
+        /// Sysprep operations to run instead of the defaults ("defaults" and
+        /// "all" select several)
+        #[arg(long, value_delimiter = ',', requires = "sysprep")]
+        ops: Vec<String>,
+
+        /// Sysprep operations not to run
+        #[arg(long, value_delimiter = ',', requires = "sysprep")]
+        skip_ops: Vec<String>,
+
+        /// List what each sysprep operation would change in the source,
+        /// without cloning
+        #[arg(long, requires = "sysprep")]
+        dry_run: bool,
+
         /// Set new hostname
         #[arg(long)]
         hostname: Option<String>,
 
-        /// Remove SSH host keys
+        /// Remove SSH host keys (a default sysprep operation; adds it to --ops)
         #[arg(long)]
         remove_keys: bool,
 
@@ -954,9 +1245,11 @@ enum Commands {
     /// Security patch analysis and CVE detection
     Patch {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
-        /// Check for CVEs in installed packages
+        /// Check installed packages against the offline vulnerability
+        /// database (see `vulndb update`)
         #[arg(long)]
         check_cves: bool,
 
@@ -968,6 +1261,15 @@ enum Commands {
         #[arg(short = 'e', long)]
         export: Option<PathBuf>,
 
+        /// Write the CVEs found as OpenVEX `affected` statements
+        #[arg(long, value_name = "FILE", requires = "check_cves")]
+        export_vex: Option<PathBuf>,
+
+        /// OpenVEX document; CVEs it marks not_affected or fixed are not
+        /// reported (repeatable)
+        #[arg(long, value_name = "FILE")]
+        vex: Vec<PathBuf>,
+
         /// Simulate package updates
         #[arg(long)]
         simulate_update: bool,
@@ -976,9 +1278,10 @@ enum Commands {
     /// Generate Software Bill of Materials (SBOM)
     Inventory {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
-        /// Output format (spdx, cyclonedx, json, csv)
+        /// Output format (spdx, spdx3, cyclonedx, cyclonedx-xml, json, csv)
         #[arg(short = 'f', long, value_name = "FORMAT", default_value = "spdx")]
         format: String,
 
@@ -994,7 +1297,8 @@ enum Commands {
         #[arg(long)]
         include_files: bool,
 
-        /// Include CVE mappings
+        /// Include CVEs from the offline vulnerability database (see
+        /// `vulndb update`)
         #[arg(long)]
         include_cves: bool,
 
@@ -1002,24 +1306,53 @@ enum Commands {
         #[arg(long, value_name = "SEVERITY")]
         severity: Option<String>,
 
+        /// OpenVEX document; CVEs it marks not_affected or fixed are left
+        /// out (repeatable)
+        #[arg(long, value_name = "FILE")]
+        vex: Vec<PathBuf>,
+
         /// Show summary before export
         #[arg(short = 'S', long)]
         summary: bool,
+
+        /// Also list the packages in each container image in the guest
+        #[arg(long)]
+        container_sboms: bool,
+
+        /// Sign an in-toto attestation of the SBOM with this PEM private
+        /// key, written to `<output>.intoto.jsonl` for cosign to verify
+        #[arg(long, value_name = "KEY", requires = "output")]
+        sign_key: Option<PathBuf>,
     },
 
     /// Validate disk image against policy
     Validate {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Policy file path (YAML)
         #[arg(short, long, value_name = "FILE")]
         policy: Option<PathBuf>,
 
-        /// Use industry benchmark (cis-ubuntu, cis-rhel, nist, pci, hipaa)
+        /// Use industry benchmark (cis-ubuntu-20.04, cis-ubuntu-22.04, cis-ubuntu-24.04,
+        /// cis-rhel-8, cis-rhel-9, nist-csf, pci-dss, hipaa)
         #[arg(short, long, value_name = "BENCHMARK")]
         benchmark: Option<String>,
 
+        /// Only check rules of this profile level or below (1 or 2)
+        #[arg(long, value_parser = clap::value_parser!(u8).range(1..=2), conflicts_with = "rego")]
+        level: Option<u8>,
+
+        /// Rego policy file or directory, evaluated against the image facts
+        /// (needs the `rego` feature, which release builds enable)
+        #[arg(long, value_name = "PATH", conflicts_with_all = ["policy", "benchmark"])]
+        rego: Option<PathBuf>,
+
+        /// Print the image facts Rego policies get as input, instead of validating
+        #[arg(long)]
+        dump_facts: bool,
+
         /// Generate example policy file
         #[arg(long)]
         example_policy: bool,
@@ -1040,6 +1373,7 @@ enum Commands {
     /// License compliance checking
     License {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Output format (text, json, csv)
@@ -1070,6 +1404,7 @@ enum Commands {
     /// Generate infrastructure-as-code blueprints
     Blueprint {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Blueprint format (terraform, ansible, kubernetes, compose)
@@ -1092,6 +1427,7 @@ enum Commands {
     /// Plan OS migrations and platform changes
     Migrate {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Migration target (os, cloud, container)
@@ -1126,6 +1462,7 @@ enum Commands {
     /// Cloud cost optimization analysis
     Cost {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Cloud provider (aws, azure, gcp)
@@ -1157,9 +1494,10 @@ enum Commands {
     /// Comprehensive security audit with detailed reporting
     Audit {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
-        /// Audit categories (permissions, users, network, services)
+        /// Audit categories (permissions, users, network, services, auditd)
         #[arg(short = 'c', long, value_delimiter = ',')]
         categories: Vec<String>,
 
@@ -1179,6 +1517,7 @@ enum Commands {
     /// Automated system repair operations
     Repair {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Repair type (permissions, packages, network, bootloader, filesystem)
@@ -1192,11 +1531,20 @@ enum Commands {
         /// Backup before repair
         #[arg(short = 'b', long)]
         backup: bool,
+
+        /// Kernel console for bootloader repair, e.g. ttyS0,115200n8 (repeatable)
+        #[arg(long = "console", value_name = "ARG")]
+        consoles: Vec<String>,
+
+        /// Leave the initramfs alone in bootloader repair
+        #[arg(long)]
+        no_virtio_initramfs: bool,
     },
 
     /// System hardening configuration
     Harden {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Hardening profile (basic, moderate, strict)
@@ -1215,6 +1563,7 @@ enum Commands {
     /// AI-powered anomaly detection
     Anomaly {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Baseline image for comparison
@@ -1237,6 +1586,7 @@ enum Commands {
     /// Smart recommendations engine
     Recommend {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Focus areas (security, performance, reliability, cost)
@@ -1255,6 +1605,7 @@ enum Commands {
     /// Dependency graph and impact analysis
     Dependencies {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Output format (text, dot, json, csv, html)
@@ -1290,149 +1641,449 @@ enum Commands {
         verbose: bool,
     },
 
-    /// Predictive analysis and capacity planning
-    Predict {
+    /// Collect kernel crash dumps, coredumps and core files
+    Crashes {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
-        /// Metric to predict (disk-growth, log-growth, package-updates)
-        #[arg(short = 'm', long, default_value = "disk-growth")]
-        metric: String,
+        /// Output format (text, json, csv)
+        #[arg(short = 'f', long, value_name = "FORMAT", default_value = "text")]
+        format: String,
 
-        /// Forecast timeframe in days
-        #[arg(short = 't', long, default_value = "30")]
-        timeframe: u32,
+        /// Output file (stdout if not specified)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
 
-        /// Export report to file
-        #[arg(short = 'e', long)]
-        export: Option<PathBuf>,
+        /// Extract crash artifacts into this directory
+        #[arg(short = 'x', long, value_name = "DIR")]
+        extract: Option<PathBuf>,
+
+        /// Show verbose output
+        #[arg(short, long)]
+        verbose: bool,
     },
 
-    /// Threat intelligence correlation and IOC detection
-    Intelligence {
+    /// Inventory setuid/setgid and world-writable files with package attribution
+    Permissions {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
-        /// Custom IOC file (STIX, OpenIOC, or CSV format)
-        #[arg(short = 'i', long)]
-        ioc_file: Option<PathBuf>,
+        /// Output format (text, json, csv)
+        #[arg(short = 'f', long, value_name = "FORMAT", default_value = "text")]
+        format: String,
 
-        /// Threat level filter (critical, high, medium, low)
-        #[arg(short = 'l', long, default_value = "medium")]
-        threat_level: String,
+        /// Output file (stdout if not specified)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
 
-        /// Enable correlation analysis
-        #[arg(short = 'c', long)]
-        correlate: bool,
+        /// Additional baseline file (YAML) of expected entries
+        #[arg(short, long, value_name = "FILE")]
+        baseline: Option<PathBuf>,
 
-        /// Export report to file
-        #[arg(short = 'e', long)]
-        export: Option<PathBuf>,
+        /// Show entries matching the baseline too
+        #[arg(long)]
+        all: bool,
+
+        /// Exit with error if anomalies or orphaned entries are found
+        #[arg(long)]
+        strict: bool,
+
+        /// Show verbose output
+        #[arg(short, long)]
+        verbose: bool,
     },
 
-    /// Change simulation and impact modeling
-    Simulate {
+    /// Report package install/upgrade/removal history and automatic updates
+    PackageHistory {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
-        /// Change type (remove-package, modify-config, disable-service, kernel-update)
-        #[arg(short = 't', long)]
-        change_type: String,
+        /// Output format (text, json, csv)
+        #[arg(short = 'f', long, value_name = "FORMAT", default_value = "text")]
+        format: String,
 
-        /// Target (package name, config file, service name, etc.)
-        #[arg(short = 'T', long)]
-        target: String,
+        /// Output file (stdout if not specified)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
 
-        /// Dry run - simulate without making changes
-        #[arg(short = 'd', long, default_value = "true")]
-        dry_run: bool,
+        /// Only show changes from the last N days
+        #[arg(long, value_name = "N")]
+        days: Option<u32>,
 
-        /// Include comprehensive risk assessment
-        #[arg(short = 'r', long)]
-        risk_assessment: bool,
+        /// Only show packages whose name contains this string
+        #[arg(short, long, value_name = "NAME")]
+        package: Option<String>,
+
+        /// Show verbose output
+        #[arg(short, long)]
+        verbose: bool,
     },
 
-    /// Comprehensive multi-dimensional risk scoring
-    Score {
+    /// Compare installed packages and files against upstream distro manifests
+    Pristine {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
-        /// Risk dimensions to check (security, compliance, reliability, performance, maintainability)
-        #[arg(short = 'd', long, value_delimiter = ',')]
-        dimensions: Vec<String>,
+        /// Mirror URL or local mirror path (distro default if not specified)
+        #[arg(short, long, value_name = "URL|PATH")]
+        mirror: Option<String>,
 
-        /// Custom weights (format: security=40,compliance=30,...)
-        #[arg(short = 'w', long)]
-        weights: Option<String>,
+        /// Packages index or repomd.xml location (repeatable, overrides mirror layout)
+        #[arg(long, value_name = "URL|PATH")]
+        manifest: Vec<String>,
 
-        /// Compare against benchmark image
-        #[arg(short = 'b', long)]
-        benchmark: Option<PathBuf>,
+        /// Skip verifying installed files against package checksums
+        #[arg(long)]
+        no_verify_files: bool,
 
-        /// Export report to file
-        #[arg(short = 'e', long)]
-        export: Option<PathBuf>,
+        /// Output format (text, json, csv)
+        #[arg(short = 'f', long, value_name = "FORMAT", default_value = "text")]
+        format: String,
+
+        /// Output file (stdout if not specified)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Show verbose output
+        #[arg(short, long)]
+        verbose: bool,
     },
 
-    /// Golden image template validation
-    Template {
+    /// Report what changed since the base image, with package attribution and likely cause
+    Provenance {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
-        /// Template type (web-server, database, docker-host, cis-level1)
-        #[arg(short = 't', long)]
-        template: String,
+        /// Base image to compare against (qcow2 backing file if not specified)
+        #[arg(short, long, value_name = "IMAGE", conflicts_with = "baseline")]
+        base: Option<PathBuf>,
 
-        /// Strict mode - fail on any violation
-        #[arg(short = 's', long)]
-        strict: bool,
+        /// Baseline recorded earlier with --record
+        #[arg(long, value_name = "FILE")]
+        baseline: Option<PathBuf>,
 
-        /// Automatically fix violations where possible
-        #[arg(short = 'f', long)]
-        fix: bool,
+        /// Record a baseline of the image to FILE instead of reporting
+        #[arg(long, value_name = "FILE", conflicts_with_all = ["base", "baseline"])]
+        record: Option<PathBuf>,
 
-        /// Export template definition to file
-        #[arg(short = 'e', long)]
-        export_template: Option<PathBuf>,
+        /// Guest directories to compare (repeatable, whole filesystem if not specified)
+        #[arg(short, long = "path", value_name = "DIR")]
+        paths: Vec<String>,
+
+        /// Output format (text, json, csv, html)
+        #[arg(short = 'f', long, value_name = "FORMAT", default_value = "text")]
+        format: String,
+
+        /// Output file (stdout if not specified)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Show verbose output
+        #[arg(short, long)]
+        verbose: bool,
     },
 
-    /// Proactive threat hunting with hypothesis-driven investigation
-    Hunt {
+    /// Check guest configuration files (fstab, sshd_config, sudoers, units, ...) for syntax errors
+    Lint {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
-        /// Threat hunting hypothesis
-        #[arg(short = 'H', long)]
-        hypothesis: String,
+        /// Guest files or directories to check
+        #[arg(required = true)]
+        paths: Vec<String>,
 
-        /// Hunting framework (mitre-attack, custom)
-        #[arg(short = 'f', long, default_value = "mitre-attack")]
-        framework: String,
+        /// File format for files that are not recognised by path
+        /// (fstab, sshd-config, sudoers, nginx, systemd-unit, systemd-dropin,
+        /// crontab, system-crontab, yaml, json, toml)
+        #[arg(short, long, value_name = "KIND")]
+        kind: Option<String>,
 
-        /// Specific techniques to hunt (comma-separated tactics)
-        #[arg(short = 't', long, value_delimiter = ',')]
-        techniques: Vec<String>,
+        /// Output format (text, json, csv)
+        #[arg(short = 'f', long, value_name = "FORMAT", default_value = "text")]
+        format: String,
 
-        /// Hunt depth (surface, shallow, deep, comprehensive)
-        #[arg(short = 'D', long, default_value = "deep")]
-        depth: String,
+        /// Output file (stdout if not specified)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
 
-        /// Export hunt report to file
-        #[arg(short = 'e', long)]
-        export: Option<PathBuf>,
+        /// Exit with error on warnings too
+        #[arg(long)]
+        strict: bool,
+
+        /// Show verbose output
+        #[arg(short, long)]
+        verbose: bool,
     },
 
-    /// Forensic incident reconstruction and attack path visualization
-    Reconstruct {
+    /// Collect a hashed forensic triage package (auth logs, histories, login records, ...)
+    Collect {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
-        /// Incident type (compromise, data-exfiltration, ransomware, generic)
-        #[arg(short = 't', long)]
-        incident_type: String,
+        /// Collection profile
+        #[arg(short, long, default_value = "forensic")]
+        profile: String,
 
-        /// Start time for analysis window
-        #[arg(short = 's', long)]
+        /// Collect only these targets (repeatable), e.g. shell-history
+        #[arg(short, long, value_name = "TARGET")]
+        target: Vec<String>,
+
+        /// Package archive path (default: <image>-triage.tar.gz)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Manifest output format (text, json)
+        #[arg(short = 'f', long, value_name = "FORMAT", default_value = "text")]
+        format: String,
+
+        /// Show verbose output
+        #[arg(short, long)]
+        verbose: bool,
+    },
+
+    /// Recover deleted files from ext2/3/4 (journal, orphans) and NTFS ($MFT) filesystems
+    Recover {
+        /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
+        image: PathBuf,
+
+        /// Directory to write recovered files and recovered.json to
+        #[arg(short, long, value_name = "DIR")]
+        output: PathBuf,
+
+        /// Recover only files under this guest path (repeatable)
+        #[arg(short, long, value_name = "PATH")]
+        path: Vec<String>,
+
+        /// Report output format (text, json)
+        #[arg(short = 'f', long, value_name = "FORMAT", default_value = "text")]
+        format: String,
+
+        /// Show verbose output
+        #[arg(short, long)]
+        verbose: bool,
+    },
+
+    /// Collect logs and configs into a redacted support bundle
+    SupportBundle {
+        /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
+        image: PathBuf,
+
+        /// Bundle archive path (default: <image>-support-bundle.tar.gz)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Index output format (text, json)
+        #[arg(short = 'f', long, value_name = "FORMAT", default_value = "text")]
+        format: String,
+
+        /// Lines kept from the end of each log file
+        #[arg(long, value_name = "N", default_value = "2000")]
+        max_lines: usize,
+
+        /// Extra redaction regex (first capture group or whole match)
+        #[arg(long, value_name = "REGEX")]
+        redact: Vec<String>,
+
+        /// Disable redaction of secrets
+        #[arg(long)]
+        no_redact: bool,
+
+        /// Show verbose output
+        #[arg(short, long)]
+        verbose: bool,
+    },
+
+    /// Aggregate validate, score and inventory results into a fleet dashboard
+    #[command(name = "fleet-report")]
+    FleetReport {
+        /// Directory of JSON results from a batch run
+        results: PathBuf,
+
+        /// Earlier run directories, for trend charts
+        #[arg(long, value_name = "DIR")]
+        history: Vec<PathBuf>,
+
+        /// Output format (html, text, json, csv)
+        #[arg(short = 'f', long, value_name = "FORMAT", default_value = "html")]
+        format: String,
+
+        /// Output file (stdout if not specified)
+        #[arg(short, long, value_name = "FILE")]
+        output: Option<PathBuf>,
+
+        /// Show verbose output
+        #[arg(short, long)]
+        verbose: bool,
+    },
+
+    /// Predictive analysis and capacity planning
+    Predict {
+        /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
+        image: PathBuf,
+
+        /// Metric to predict (disk-growth, log-growth, package-updates)
+        #[arg(short = 'm', long, default_value = "disk-growth")]
+        metric: String,
+
+        /// Forecast timeframe in days
+        #[arg(short = 't', long, default_value = "30")]
+        timeframe: u32,
+
+        /// Export report to file
+        #[arg(short = 'e', long)]
+        export: Option<PathBuf>,
+    },
+
+    /// Threat intelligence correlation and IOC detection
+    Intelligence {
+        /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
+        image: PathBuf,
+
+        /// IOC file: STIX 2.1 bundle, OpenIOC or CSV (repeatable)
+        #[arg(short = 'i', long, required = true)]
+        ioc_file: Vec<PathBuf>,
+
+        /// Threat level filter (critical, high, medium, low)
+        #[arg(short = 'l', long, default_value = "medium")]
+        threat_level: String,
+
+        /// Enable correlation analysis
+        #[arg(short = 'c', long)]
+        correlate: bool,
+
+        /// Export report to file
+        #[arg(short = 'e', long)]
+        export: Option<PathBuf>,
+
+        /// Write the matches as an ATT&CK Navigator layer
+        #[arg(long)]
+        attack_layer: Option<PathBuf>,
+    },
+
+    /// Change simulation and impact modeling
+    Simulate {
+        /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
+        image: PathBuf,
+
+        /// Change type (remove-package, modify-config, disable-service, kernel-update)
+        #[arg(short = 't', long)]
+        change_type: String,
+
+        /// Target (package name, config file, service name, etc.)
+        #[arg(short = 'T', long)]
+        target: String,
+
+        /// Dry run - simulate without making changes
+        #[arg(short = 'd', long, default_value = "true")]
+        dry_run: bool,
+
+        /// Include comprehensive risk assessment
+        #[arg(short = 'r', long)]
+        risk_assessment: bool,
+    },
+
+    /// Comprehensive multi-dimensional risk scoring
+    Score {
+        /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
+        image: PathBuf,
+
+        /// Risk dimensions to check (security, compliance, reliability, performance, maintainability)
+        #[arg(short = 'd', long, value_delimiter = ',')]
+        dimensions: Vec<String>,
+
+        /// Custom weights (format: security=40,compliance=30,...)
+        #[arg(short = 'w', long)]
+        weights: Option<String>,
+
+        /// Compare against benchmark image
+        #[arg(short = 'b', long)]
+        benchmark: Option<PathBuf>,
+
+        /// Export report to file (Markdown, or JSON for a .json path)
+        #[arg(short = 'e', long)]
+        export: Option<PathBuf>,
+    },
+
+    /// Golden image template validation
+    Template {
+        /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
+        image: PathBuf,
+
+        /// Template type (web-server, database, docker-host, cis-level1)
+        #[arg(short = 't', long)]
+        template: String,
+
+        /// Strict mode - fail on any violation
+        #[arg(short = 's', long)]
+        strict: bool,
+
+        /// Automatically fix violations where possible
+        #[arg(short = 'f', long)]
+        fix: bool,
+
+        /// Export template definition to file
+        #[arg(short = 'e', long)]
+        export_template: Option<PathBuf>,
+    },
+
+    /// Proactive threat hunting with hypothesis-driven investigation
+    Hunt {
+        /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
+        image: PathBuf,
+
+        /// Threat hunting hypothesis
+        #[arg(short = 'H', long)]
+        hypothesis: String,
+
+        /// Hunting framework (mitre-attack, custom)
+        #[arg(short = 'f', long, default_value = "mitre-attack")]
+        framework: String,
+
+        /// Tactics or technique IDs to hunt (comma-separated, e.g.
+        /// persistence,T1552)
+        #[arg(short = 't', long, value_delimiter = ',')]
+        techniques: Vec<String>,
+
+        /// Hunt depth (surface, shallow, deep, comprehensive)
+        #[arg(short = 'D', long, default_value = "deep")]
+        depth: String,
+
+        /// Export hunt report to file
+        #[arg(short = 'e', long)]
+        export: Option<PathBuf>,
+
+        /// Write the evidence as an ATT&CK Navigator layer
+        #[arg(long)]
+        attack_layer: Option<PathBuf>,
+    },
+
+    /// Forensic incident reconstruction and attack path visualization
+    Reconstruct {
+        /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
+        image: PathBuf,
+
+        /// Incident type (compromise, data-exfiltration, ransomware, generic)
+        #[arg(short = 't', long)]
+        incident_type: String,
+
+        /// Start time for analysis window
+        #[arg(short = 's', long)]
         start_time: Option<String>,
 
         /// End time for analysis window
@@ -1451,6 +2102,7 @@ enum Commands {
     /// Automated progressive system evolution and self-improvement
     Evolve {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Target state (hardened, optimized, compliant, production-ready)
@@ -1477,6 +2129,7 @@ enum Commands {
     /// Zero-trust continuous verification and supply chain integrity
     Verify {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Verification level (basic, standard, strict, paranoid)
@@ -1500,6 +2153,17 @@ enum Commands {
         export: Option<PathBuf>,
     },
 
+    /// Check host prerequisites (kernel modules, devices, tools, disk space)
+    Doctor {
+        /// Output format (text, json)
+        #[arg(short = 'f', long, value_name = "FORMAT", default_value = "text")]
+        format: String,
+
+        /// Show passing checks too
+        #[arg(short, long)]
+        verbose: bool,
+    },
+
     /// Show version information
     Version,
 
@@ -1507,6 +2171,7 @@ enum Commands {
     #[command(alias = "repl")]
     Interactive {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
     },
 
@@ -1514,6 +2179,7 @@ enum Commands {
     #[command(alias = "ex")]
     Explore {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Starting path in VM filesystem (default: /)
@@ -1525,6 +2191,7 @@ enum Commands {
     #[command(alias = "batch")]
     Script {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Script file with commands (one per line)
@@ -1539,6 +2206,7 @@ enum Commands {
     #[command(name = "systemd-journal")]
     SystemdJournal {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Filter by priority (0=emerg, 3=err, 4=warning, 6=info)
@@ -1564,12 +2232,29 @@ enum Commands {
         /// Limit number of entries
         #[arg(short, long)]
         limit: Option<usize>,
+
+        /// Only this boot: a boot ID, or an offset (0 last, -1 previous, 1 first)
+        #[arg(short, long, allow_hyphen_values = true)]
+        boot: Option<String>,
+
+        /// List the boots found in the journal
+        #[arg(long)]
+        list_boots: bool,
+
+        /// Check journal files for damage and unsealed entries
+        #[arg(long)]
+        verify: bool,
+
+        /// FSS verification key to check seals with (implies --verify)
+        #[arg(long, value_name = "KEY")]
+        verify_key: Option<String>,
     },
 
     /// Analyze systemd services and dependencies
     #[command(name = "systemd-services")]
     SystemdServices {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Show dependency tree for specific service
@@ -1593,6 +2278,7 @@ enum Commands {
     #[command(name = "systemd-boot")]
     SystemdBoot {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Show boot timeline diagram
@@ -1610,12 +2296,21 @@ enum Commands {
         /// Number of slowest services to show
         #[arg(short = 'n', long, default_value = "10")]
         top: usize,
+
+        /// Check recorded boot timings against a YAML budget file (exits 1 when over budget)
+        #[arg(long, value_name = "FILE")]
+        budget: Option<PathBuf>,
+
+        /// Output format for --budget (text, json)
+        #[arg(short, long, value_name = "FORMAT")]
+        output: Option<String>,
     },
 
     /// Interactive TUI for VM inspection with orange color theme
     #[command(alias = "ui")]
     Tui {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
     },
 
@@ -1623,12 +2318,14 @@ enum Commands {
     #[command(alias = "sh")]
     Shell {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
     },
 
     /// AI-powered diagnostics and assistance (requires --features ai and OPENAI_API_KEY)
     Ai {
         /// Disk image path
+        #[arg(value_parser = parse_image_ref)]
         image: PathBuf,
 
         /// Question or problem description
@@ -1645,6 +2342,63 @@ enum Commands {
 
     /// Manage fix plans (preview, validate, export, apply)
     Plan(PlanCommand),
+
+    /// Show, verify and export the log of operations that changed images
+    AuditLog(AuditLogCommand),
+
+    /// Manage the local catalog of known images
+    Catalog(CatalogCommand),
+
+    /// List, export, create and clear qcow2 dirty bitmaps for incremental backups
+    Bitmap(BitmapCommand),
+
+    /// Show the backing-file chain of an image with sizes and formats
+    Chain(ChainCommand),
+
+    /// Point an image at a new backing file (safe or --unsafe)
+    Rebase(RebaseCommand),
+
+    /// Unpack an OVA, convert its disks and describe its CPUs, memory and NICs
+    ImportOva(ImportOvaCommand),
+
+    /// Generate a libvirt domain or QEMU command line that boots an image
+    Generate(GenerateCommand),
+
+    /// Share a host directory or files from an image with a running VM (virtiofs, 9p)
+    Share(ShareCommand),
+
+    /// Check partition alignment and sector sizes, and realign legacy layouts
+    AlignCheck(AlignCheckCommand),
+
+    /// Convert between BIOS/MBR and UEFI/GPT boot (ESP, bootloader, fstab)
+    ConvertBoot(ConvertBootCommand),
+
+    /// Customize a guest offline: hostname, users, SSH keys, files, first-boot scripts, cloud-init
+    Customize(CustomizeCommand),
+
+    /// Copy a directory from one image into another, writing only changed file chunks
+    Sync(SyncCommand),
+
+    /// Rerun an inspection from a trace recorded with --record-trace
+    Replay(ReplayCommand),
+
+    /// Download the OSV/OVAL feeds and query the offline vulnerability database
+    Vulndb(VulndbCommand),
+
+    /// Push and pull disk images as OCI registry artifacts
+    #[cfg(feature = "oci")]
+    Oci(OciCommand),
+
+    /// List external commands (guestctl-NAME executables on PATH)
+    Plugins {
+        /// Output format (text, json)
+        #[arg(short = 'f', long, value_name = "FORMAT", default_value = "text")]
+        format: String,
+    },
+
+    /// Run guestctl-NAME from PATH
+    #[command(external_subcommand)]
+    External(Vec<OsString>),
 }
 
 #[derive(clap::ValueEnum, Clone)]
@@ -1684,6 +2438,8 @@ fn run_standalone_explorer(image_path: &PathBuf, start_path: &str, verbose: bool
         false,
         None
     ).context("Failed to add drive")?;
+    cli::target::add_disks(&mut guestfs, false)?;
+    cli::target::select(&mut guestfs);
 
     guestfs.launch().context("Failed to launch guestfs")?;
 
@@ -1741,7 +2497,31 @@ fn run_standalone_explorer(image_path: &PathBuf, start_path: &str, verbose: bool
 }
 
 fn main() -> anyhow::Result<()> {
-    let cli = Cli::parse();
+    let matches = Cli::command().get_matches();
+    let cli = Cli::from_arg_matches(&matches).unwrap_or_else(|e| e.exit());
+
+    let notifier = cli::notify::Notifier::start(
+        matches.subcommand_name().unwrap_or("guestctl"),
+        &cli.notify,
+        cli.notify_after,
+    )?;
+    let record_trace = cli.record_trace.clone();
+    if record_trace.is_some() {
+        guestkit::guestfs::session_trace::start_recording();
+    }
+    let outcome = run(cli);
+    // Failed commands are saved too; they are what bug reports are about
+    if let Some(path) = &record_trace {
+        if let Err(e) = cli::replay::save_recording(path) {
+            eprintln!("{} {:#}", plain::text("⚠").yellow(), e);
+        }
+    }
+    notifier.finish(&outcome);
+    outcome
+}
+
+fn run(cli: Cli) -> anyhow::Result<()> {
+    cli::i18n::init(cli.lang.as_deref());
 
     // Setup global environment variables
     if cli.debug {
@@ -1751,13 +2531,20 @@ fn main() -> anyhow::Result<()> {
         }
     }
 
-    if cli.no_color || cli.machine_readable {
+    if cli.no_color || cli.machine_readable || cli.plain {
         // SAFETY: Setting an environment variable in single-threaded initialization is safe
         unsafe {
             std::env::set_var("NO_COLOR", "1");
         }
     }
 
+    if cli.plain {
+        // SAFETY: Setting an environment variable in single-threaded initialization is safe
+        unsafe {
+            std::env::set_var(cli::plain::PLAIN_ENV, "1");
+        }
+    }
+
     if cli.read_only {
         // SAFETY: Setting an environment variable in single-threaded initialization is safe
         unsafe {
@@ -1772,6 +2559,22 @@ fn main() -> anyhow::Result<()> {
         }
     }
 
+    if !cli.keys.is_empty() {
+        cli::keys::install(&cli.keys);
+    }
+
+    if let Some(selector) = &cli.os_root {
+        cli::target::set_os_root(selector.clone());
+    }
+
+    if !cli.extra_disks.is_empty() {
+        cli::target::set_disks(cli.extra_disks.clone());
+    }
+
+    if cli.mount_options.is_some() {
+        guestkit::guestfs::mount_plan::set_default_fstab_options(cli.mount_options.clone());
+    }
+
     if cli.timeout > 0 {
         // SAFETY: Setting an environment variable in single-threaded initialization is safe
         unsafe {
@@ -1799,9 +2602,24 @@ fn main() -> anyhow::Result<()> {
 
     logger.init();
 
+    // Plain mode covers report output; full-screen and line-editing modes
+    // draw their own screens, and machine-readable output is left as is
+    let interactive = matches!(
+        cli.command,
+        Commands::Interactive { .. }
+            | Commands::Explore { .. }
+            | Commands::Tui { .. }
+            | Commands::Shell { .. }
+            | Commands::External(_)
+    );
+    if cli.plain && !interactive && !cli.machine_readable {
+        cli::plain::enable();
+    }
+
     match cli.command {
         Commands::Inspect {
             image,
+            mut disks,
             output,
             profile,
             export,
@@ -1822,15 +2640,20 @@ fn main() -> anyhow::Result<()> {
                 .transpose()
                 .map_err(|e| anyhow::anyhow!("{}", e))?;
 
+            // --disk disks too, so caching sees them
+            disks.extend(cli.extra_disks.iter().cloned());
             inspect_image(
                 &image,
+                &disks,
                 cli.verbose,
                 cli.debug,
                 output_format,
                 profile,
                 export,
                 export_output,
-                !no_cache,  // Cache enabled by default, disabled with --no-cache
+                // Cache enabled by default, disabled with --no-cache; a cached
+                // report would leave nothing to record
+                !no_cache && cli.record_trace.is_none(),
                 cache_refresh,
             )?;
         }
@@ -1866,10 +2689,11 @@ fn main() -> anyhow::Result<()> {
             filter,
             directories_only,
             limit,
+            exclude,
+            exclude_from,
+            ignore_case,
         } => {
-            list_files_enhanced(
-                &image,
-                &path,
+            let options = ListOptions {
                 recursive,
                 long,
                 all,
@@ -1878,9 +2702,12 @@ fn main() -> anyhow::Result<()> {
                 reverse,
                 filter,
                 directories_only,
-                limit,
-                cli.verbose,
-            )?;
+                limit,
+                exclude,
+                exclude_from,
+                ignore_case,
+            };
+            list_files_enhanced(&image, &path, options, cli.verbose)?;
         }
 
         Commands::Extract {
@@ -1914,8 +2741,26 @@ fn main() -> anyhow::Result<()> {
             image,
             path,
             output,
+            format,
+            include,
+            exclude,
+            mtime,
+            level,
         } => {
-            backup_files(&image, &path, &output, cli.verbose)?;
+            let options = guestkit::guestfs::ArchiveOptions {
+                include,
+                exclude,
+                mtime,
+                level,
+            };
+            backup_files(
+                &image,
+                &path,
+                &output,
+                format.as_deref(),
+                &options,
+                cli.verbose,
+            )?;
         }
 
         Commands::Create { path, size, format } => {
@@ -1937,19 +2782,36 @@ fn main() -> anyhow::Result<()> {
             compress,
             flatten,
             progress: _,
-            verify: _,
+            verify,
+            manifest,
+            verify_report,
+            resume,
             sparse: _,
             preallocate: _,
             compression_level: _,
             buffer_size: _,
+            queue_depth,
+            bwlimit,
+            ionice,
+            #[cfg(feature = "publish")]
+            publish,
         } => {
             log::info!("Converting {} -> {}", source.display(), output.display());
 
-            let converter = DiskConverter::new();
+            let converter = DiskConverter::new().with_copy_options(CopyOptions {
+                jobs: cli.jobs.unwrap_or(copy::DEFAULT_JOBS),
+                queue_depth,
+                bwlimit: bwlimit.map(|mb| mb * 1_000_000),
+                io_priority: ionice,
+            }).with_verify(verify).with_resume(resume);
+            let converter = match &manifest {
+                Some(path) => converter.with_manifest_path(path),
+                None => converter,
+            };
             let result = converter.convert(&source, &output, &format, compress, flatten)?;
 
             if result.success {
-                println!("✓ Conversion successful!");
+                println!("{} Conversion successful!", plain::text("✓"));
                 println!(
                     "  Source:  {} ({})",
                     source.display(),
@@ -1962,8 +2824,35 @@ fn main() -> anyhow::Result<()> {
                 );
                 println!("  Size:    {} bytes", result.output_size);
                 println!("  Time:    {:.2}s", result.duration_secs);
+
+                if let Some(report) = &result.verification {
+                    if let Some(path) = &verify_report {
+                        std::fs::write(path, serde_json::to_string_pretty(report)?)?;
+                    }
+                    if report.verified {
+                        println!("  Verify:  {} blocks match (sha256 {})", report.blocks, report.source_digest);
+                    } else {
+                        eprintln!(
+                            "{} Verification failed: {} of {} blocks differ",
+                            plain::text("✗"),
+                            report.mismatches.len(),
+                            report.blocks
+                        );
+                        for mismatch in report.mismatches.iter().take(10) {
+                            eprintln!("    block {} at offset {}", mismatch.index, mismatch.offset);
+                        }
+                        std::process::exit(1);
+                    }
+                }
+
+                #[cfg(feature = "publish")]
+                cli::publish::publish_image(&output, result.output_format.as_str(), &publish)?;
             } else {
-                eprintln!("✗ Conversion failed: {}", result.error.unwrap_or_default());
+                eprintln!(
+                    "{} Conversion failed: {}",
+                    plain::text("✗"),
+                    result.error.unwrap_or_default()
+                );
                 std::process::exit(1);
             }
         }
@@ -1972,7 +2861,15 @@ fn main() -> anyhow::Result<()> {
             let converter = DiskConverter::new();
             let format = converter.detect_format(&image)?;
 
-            println!("Detected format: {}", format.as_str());
+            if format == DiskFormat::Vmdk {
+                let vmdk = VmdkImage::open(&image)?;
+                println!("Detected format: vmdk ({})", vmdk.subformat);
+                for layer in vmdk.backing_chain()? {
+                    println!("  Parent: {} ({})", layer.path.display(), layer.subformat);
+                }
+            } else {
+                println!("Detected format: {}", format.as_str());
+            }
         }
 
         Commands::Info { image } => {
@@ -1983,19 +2880,44 @@ fn main() -> anyhow::Result<()> {
         }
 
         Commands::InspectBatch {
-            images,
+            mut images,
+            tags,
+            journal,
+            resume,
             parallel,
             output,
             no_cache,
         } => {
             use cli::formatters::OutputFormat;
+            use cli::journal::Journal;
             let output_format = output
                 .as_ref()
                 .map(|s| s.parse::<OutputFormat>())
                 .transpose()
                 .map_err(|e| anyhow::anyhow!("{}", e))?;
 
-            inspect_batch(&images, parallel, cli.verbose, output_format, !no_cache)?;  // Cache enabled by default
+            if !tags.is_empty() {
+                let catalog = cli::catalog::Catalog::load_default()?;
+                let selected = catalog.select(&tags);
+                if selected.is_empty() {
+                    anyhow::bail!("No cataloged images tagged {}", tags.join(", "));
+                }
+                images.extend(selected.iter().map(|entry| PathBuf::from(&entry.location)));
+            }
+
+            let journal = match resume {
+                Some(path) => Journal::resume(&path, "inspect-batch")?,
+                None => {
+                    let items: Vec<String> = images
+                        .iter()
+                        .map(|image| image.to_string_lossy().to_string())
+                        .collect();
+                    let path = journal.unwrap_or_else(|| Journal::default_path("inspect-batch"));
+                    Journal::create(&path, "inspect-batch", &items)?
+                }
+            };
+
+            inspect_batch(&journal, parallel, cli.verbose, output_format, !no_cache)?;  // Cache enabled by default
         }
 
         Commands::CacheClear => {
@@ -2003,7 +2925,7 @@ fn main() -> anyhow::Result<()> {
             let cache = InspectionCache::new()?;
             let count = cache.clear_all()?;
 
-            println!("✓ Cleared {} cached inspection results", count);
+            println!("{} Cleared {} cached inspection results", plain::text("✓"), count);
         }
 
         Commands::CacheStats => {
@@ -2016,8 +2938,12 @@ fn main() -> anyhow::Result<()> {
             println!("  Total Size: {}", stats.size_human());
         }
 
-        Commands::Filesystems { image, detailed } => {
-            list_filesystems(&image, detailed, cli.verbose)?;
+        Commands::Filesystems {
+            image,
+            detailed,
+            subvolumes,
+        } => {
+            list_filesystems(&image, detailed, subvolumes, cli.verbose)?;
         }
 
         Commands::Packages {
@@ -2034,8 +2960,16 @@ fn main() -> anyhow::Result<()> {
             path,
             line_numbers,
             show_all,
+            max_bytes,
         } => {
-            cat_file_enhanced(&image, &path, line_numbers, show_all, cli.verbose)?;
+            cat_file_enhanced(
+                &image,
+                &path,
+                line_numbers,
+                show_all,
+                max_bytes,
+                cli.verbose,
+            )?;
         }
 
         Commands::Search {
@@ -2048,6 +2982,8 @@ fn main() -> anyhow::Result<()> {
             file_type,
             max_depth,
             limit,
+            exclude,
+            exclude_from,
         } => {
             search_command(
                 &image,
@@ -2059,6 +2995,8 @@ fn main() -> anyhow::Result<()> {
                 file_type,
                 max_depth,
                 limit,
+                exclude,
+                exclude_from,
                 cli.verbose,
             )?;
         }
@@ -2075,11 +3013,9 @@ fn main() -> anyhow::Result<()> {
             before_context,
             after_context,
             max_count,
+            max_bytes,
         } => {
-            grep_command(
-                &image,
-                &pattern,
-                &path,
+            let options = GrepCommandOptions {
                 ignore_case,
                 line_numbers,
                 recursive,
@@ -2088,8 +3024,9 @@ fn main() -> anyhow::Result<()> {
                 before_context,
                 after_context,
                 max_count,
-                cli.verbose,
-            )?;
+                max_bytes,
+            };
+            grep_command(&image, &pattern, &path, &options, cli.verbose)?;
         }
 
         Commands::Hash {
@@ -2109,8 +3046,18 @@ fn main() -> anyhow::Result<()> {
             output,
             report,
             check_cve,
+            vex,
         } => {
-            scan_command(&image, &scan_type, severity, output, report, check_cve, cli.verbose)?;
+            scan_command(
+                &image,
+                &scan_type,
+                severity,
+                output,
+                report,
+                check_cve,
+                &vex,
+                cli.verbose,
+            )?;
         }
 
         Commands::Benchmark {
@@ -2195,9 +3142,20 @@ fn main() -> anyhow::Result<()> {
             start_time,
             end_time,
             sources,
+            path,
             format,
+            output,
         } => {
-            timeline_command(&image, start_time, end_time, sources, &format, cli.verbose)?;
+            timeline_command(
+                &image,
+                start_time,
+                end_time,
+                sources,
+                path,
+                &format,
+                output,
+                cli.verbose,
+            )?;
         }
 
         Commands::Fingerprint {
@@ -2232,11 +3190,32 @@ fn main() -> anyhow::Result<()> {
             image,
             scan_paths,
             patterns,
+            include,
             exclude,
+            exclude_from,
+            ignore_case,
+            rule_packs,
+            allowlists,
+            baseline,
+            write_baseline,
             show_content,
             export,
         } => {
-            secrets_command(&image, scan_paths, patterns, exclude, show_content, export, cli.verbose)?;
+            let options = SecretsOptions {
+                scan_paths,
+                patterns,
+                include,
+                exclude,
+                exclude_from,
+                ignore_case,
+                rule_packs,
+                allowlists,
+                baseline,
+                write_baseline,
+                show_content,
+                export,
+            };
+            secrets_command(&image, options, cli.verbose)?;
         }
 
         Commands::Rescue {
@@ -2247,7 +3226,15 @@ fn main() -> anyhow::Result<()> {
             force,
             backup,
         } => {
-            rescue_command(&image, &operation, user, password, force, backup, cli.verbose)?;
+            let parameters = serde_json::json!({
+                "operation": operation,
+                "user": user,
+                "force": force,
+                "backup": backup,
+            });
+            audited("rescue", &image, parameters, || {
+                rescue_command(&image, &operation, user, password, force, backup, cli.verbose)
+            })?;
         }
 
         Commands::Optimize {
@@ -2255,8 +3242,18 @@ fn main() -> anyhow::Result<()> {
             operations,
             aggressive,
             dry_run,
+            exclude,
+            exclude_from,
         } => {
-            optimize_command(&image, operations, aggressive, dry_run, cli.verbose)?;
+            optimize_command(
+                &image,
+                operations,
+                aggressive,
+                dry_run,
+                exclude,
+                exclude_from,
+                cli.verbose,
+            )?;
         }
 
         Commands::Network {
@@ -2284,9 +3281,22 @@ fn main() -> anyhow::Result<()> {
             deep_scan,
             check_rootkits,
             yara_rules,
+            yara_paths,
             quarantine,
+            format,
+            attack_layer,
         } => {
-            malware_command(&image, deep_scan, check_rootkits, yara_rules, quarantine, cli.verbose)?;
+            malware_command(
+                &image,
+                deep_scan,
+                check_rootkits,
+                yara_rules,
+                yara_paths,
+                quarantine,
+                &format,
+                attack_layer,
+                cli.verbose,
+            )?;
         }
 
         Commands::Health {
@@ -2302,11 +3312,30 @@ fn main() -> anyhow::Result<()> {
             source,
             dest,
             sysprep,
+            mut ops,
+            mut skip_ops,
+            dry_run,
             hostname,
             remove_keys,
             preserve_users,
         } => {
-            clone_command(&source, &dest, sysprep, hostname, remove_keys, preserve_users, cli.verbose)?;
+            if remove_keys && !ops.is_empty() {
+                ops.push("ssh-hostkeys".to_string());
+            }
+            if preserve_users {
+                skip_ops.extend(["bash-history", "zsh-history"].map(String::from));
+            }
+            let sysprep_ops = sysprep
+                .then(|| {
+                    guestkit::guestfs::sysprep_catalog::select_sysprep_operations(&ops, &skip_ops)
+                })
+                .transpose()?;
+            if dry_run {
+                let sysprep_ops = sysprep_ops.unwrap_or_default();
+                sysprep_dry_run(&source, &sysprep_ops, cli.verbose)?;
+            } else {
+                clone_command(&source, &dest, sysprep_ops, hostname, cli.verbose)?;
+            }
         }
 
         Commands::Patch {
@@ -2314,9 +3343,20 @@ fn main() -> anyhow::Result<()> {
             check_cves,
             severity,
             export,
+            export_vex,
+            vex,
             simulate_update,
         } => {
-            patch_command(&image, check_cves, severity, export, simulate_update, cli.verbose)?;
+            patch_command(
+                &image,
+                check_cves,
+                severity,
+                export,
+                export_vex,
+                &vex,
+                simulate_update,
+                cli.verbose,
+            )?;
         }
 
         Commands::Inventory {
@@ -2327,7 +3367,10 @@ fn main() -> anyhow::Result<()> {
             include_files,
             include_cves,
             severity,
+            vex,
             summary,
+            container_sboms,
+            sign_key,
         } => {
             inventory_command(
                 &image,
@@ -2337,7 +3380,10 @@ fn main() -> anyhow::Result<()> {
                 include_files,
                 include_cves,
                 severity,
+                &vex,
                 summary,
+                container_sboms,
+                sign_key.as_deref(),
                 cli.verbose,
             )?;
         }
@@ -2346,6 +3392,9 @@ fn main() -> anyhow::Result<()> {
             image,
             policy,
             benchmark,
+            level,
+            rego,
+            dump_facts,
             example_policy,
             format,
             output,
@@ -2355,6 +3404,9 @@ fn main() -> anyhow::Result<()> {
                 &image,
                 policy.as_deref(),
                 benchmark,
+                level,
+                rego.as_deref(),
+                dump_facts,
                 example_policy,
                 &format,
                 output.as_deref(),
@@ -2481,8 +3533,31 @@ fn main() -> anyhow::Result<()> {
             repair_type,
             force,
             backup,
+            consoles,
+            no_virtio_initramfs,
         } => {
-            repair_command(&image, &repair_type, force, backup, cli.verbose)?;
+            let parameters = serde_json::json!({
+                "repair_type": repair_type,
+                "force": force,
+                "backup": backup,
+                "consoles": consoles,
+                "virtio_initramfs": !no_virtio_initramfs,
+            });
+            let boot_options = guestkit::guestfs::BootRepairOptions {
+                consoles,
+                virtio_initramfs: !no_virtio_initramfs,
+                ..Default::default()
+            };
+            audited("repair", &image, parameters, || {
+                repair_command(
+                    &image,
+                    &repair_type,
+                    force,
+                    backup,
+                    &boot_options,
+                    cli.verbose,
+                )
+            })?;
         }
 
         Commands::Harden {
@@ -2537,6 +3612,189 @@ fn main() -> anyhow::Result<()> {
             )?;
         }
 
+        Commands::Crashes {
+            image,
+            format,
+            output,
+            extract,
+            verbose,
+        } => {
+            crashes_command(
+                &image,
+                &format,
+                output.as_deref(),
+                extract.as_deref(),
+                verbose || cli.verbose,
+            )?;
+        }
+
+        Commands::Permissions {
+            image,
+            format,
+            output,
+            baseline,
+            all,
+            strict,
+            verbose,
+        } => {
+            permissions_command(
+                &image,
+                &format,
+                output.as_deref(),
+                baseline.as_deref(),
+                all,
+                strict,
+                verbose || cli.verbose,
+            )?;
+        }
+
+        Commands::PackageHistory {
+            image,
+            format,
+            output,
+            days,
+            package,
+            verbose,
+        } => {
+            package_history_command(
+                &image,
+                &format,
+                output.as_deref(),
+                days,
+                package.as_deref(),
+                verbose || cli.verbose,
+            )?;
+        }
+
+        Commands::Pristine {
+            image,
+            mirror,
+            manifest,
+            no_verify_files,
+            format,
+            output,
+            verbose,
+        } => {
+            pristine_command(
+                &image,
+                mirror,
+                manifest,
+                !no_verify_files,
+                &format,
+                output.as_deref(),
+                verbose || cli.verbose,
+            )?;
+        }
+
+        Commands::Provenance {
+            image,
+            base,
+            baseline,
+            record,
+            paths,
+            format,
+            output,
+            verbose,
+        } => {
+            let options = ProvenanceCommandOptions {
+                base,
+                baseline,
+                record,
+                paths,
+            };
+            provenance_command(
+                &image,
+                options,
+                &format,
+                output.as_deref(),
+                verbose || cli.verbose,
+            )?;
+        }
+
+        Commands::Lint {
+            image,
+            paths,
+            kind,
+            format,
+            output,
+            strict,
+            verbose,
+        } => {
+            lint_command(
+                &image,
+                &paths,
+                kind.as_deref(),
+                &format,
+                output.as_deref(),
+                strict,
+                verbose || cli.verbose,
+            )?;
+        }
+
+        Commands::Collect {
+            image,
+            profile,
+            target,
+            output,
+            format,
+            verbose,
+        } => {
+            collect_command(
+                &image,
+                &profile,
+                &target,
+                output.as_deref(),
+                &format,
+                verbose || cli.verbose,
+            )?;
+        }
+
+        Commands::Recover {
+            image,
+            output,
+            path,
+            format,
+            verbose,
+        } => {
+            recover_command(&image, &output, &path, &format, verbose || cli.verbose)?;
+        }
+
+        Commands::SupportBundle {
+            image,
+            output,
+            format,
+            max_lines,
+            redact,
+            no_redact,
+            verbose,
+        } => {
+            support_bundle_command(
+                &image,
+                output.as_deref(),
+                &format,
+                max_lines,
+                &redact,
+                no_redact,
+                verbose || cli.verbose,
+            )?;
+        }
+
+        Commands::FleetReport {
+            results,
+            history,
+            format,
+            output,
+            verbose,
+        } => {
+            fleet_report_command(
+                &results,
+                &history,
+                &format,
+                output.as_deref(),
+                verbose || cli.verbose,
+            )?;
+        }
+
         Commands::Predict {
             image,
             metric,
@@ -2552,8 +3810,17 @@ fn main() -> anyhow::Result<()> {
             threat_level,
             correlate,
             export,
+            attack_layer,
         } => {
-            intelligence_command(&image, ioc_file, &threat_level, correlate, export, cli.verbose)?;
+            intelligence_command(
+                &image,
+                ioc_file,
+                &threat_level,
+                correlate,
+                export,
+                attack_layer,
+                cli.verbose,
+            )?;
         }
 
         Commands::Simulate {
@@ -2593,8 +3860,18 @@ fn main() -> anyhow::Result<()> {
             techniques,
             depth,
             export,
+            attack_layer,
         } => {
-            hunt_command(&image, hypothesis, &framework, techniques, &depth, export, cli.verbose)?;
+            hunt_command(
+                &image,
+                hypothesis,
+                &framework,
+                techniques,
+                &depth,
+                export,
+                attack_layer,
+                cli.verbose,
+            )?;
         }
 
         Commands::Reconstruct {
@@ -2630,6 +3907,10 @@ fn main() -> anyhow::Result<()> {
             verify_command(&image, &verification_level, check_supply_chain, check_identity, check_integrity, export, cli.verbose)?;
         }
 
+        Commands::Doctor { format, verbose } => {
+            doctor_command(&format, verbose || cli.verbose)?;
+        }
+
         Commands::Version => {
             println!("guestctl {}", VERSION);
             println!("A modern VM disk inspection and manipulation toolkit");
@@ -2666,6 +3947,10 @@ fn main() -> anyhow::Result<()> {
             warnings,
             stats,
             limit,
+            boot,
+            list_boots,
+            verify,
+            verify_key,
         } => {
             systemd_journal_command(
                 &image,
@@ -2675,6 +3960,10 @@ fn main() -> anyhow::Result<()> {
                 warnings,
                 stats,
                 limit,
+                boot.as_deref(),
+                list_boots,
+                verify || verify_key.is_some(),
+                verify_key.as_deref(),
                 cli.verbose,
             )?;
         }
@@ -2702,8 +3991,14 @@ fn main() -> anyhow::Result<()> {
             recommendations,
             summary,
             top,
+            budget,
+            output,
         } => {
-            systemd_boot_command(&image, timeline, recommendations, summary, top, cli.verbose)?;
+            if let Some(budget) = budget {
+                systemd_boot_budget_command(&image, &budget, output.as_deref(), cli.verbose)?;
+            } else {
+                systemd_boot_command(&image, timeline, recommendations, summary, top, cli.verbose)?;
+            }
         }
 
         Commands::Tui { image } => {
@@ -2734,6 +4029,103 @@ fn main() -> anyhow::Result<()> {
         Commands::Plan(plan_cmd) => {
             plan_cmd.execute()?;
         }
+
+        Commands::AuditLog(audit_cmd) => {
+            audit_cmd.execute()?;
+        }
+
+        Commands::Catalog(catalog_cmd) => {
+            catalog_cmd.execute()?;
+        }
+
+        Commands::Bitmap(bitmap_cmd) => {
+            bitmap_cmd.execute()?;
+        }
+
+        Commands::Chain(chain_cmd) => {
+            chain_cmd.execute()?;
+        }
+
+        Commands::Rebase(rebase_cmd) => {
+            rebase_cmd.execute()?;
+        }
+
+        Commands::ImportOva(import_cmd) => {
+            import_cmd.execute()?;
+        }
+
+        Commands::Generate(generate_cmd) => {
+            generate_cmd.execute()?;
+        }
+
+        Commands::Share(share_cmd) => {
+            share_cmd.execute()?;
+        }
+
+        Commands::AlignCheck(align_cmd) => {
+            align_cmd.execute()?;
+        }
+
+        Commands::ConvertBoot(convert_boot_cmd) => {
+            convert_boot_cmd.execute()?;
+        }
+
+        Commands::Customize(customize_cmd) => {
+            customize_cmd.execute()?;
+        }
+
+        Commands::Sync(sync_cmd) => {
+            sync_cmd.execute()?;
+        }
+
+        Commands::Replay(replay_cmd) => {
+            replay_cmd.execute()?;
+        }
+
+        Commands::Vulndb(vulndb_cmd) => {
+            vulndb_cmd.execute()?;
+        }
+
+        #[cfg(feature = "oci")]
+        Commands::Oci(oci_cmd) => {
+            oci_cmd.execute()?;
+        }
+
+        Commands::Plugins { format } => {
+            let builtins: Vec<String> = Cli::command()
+                .get_subcommands()
+                .flat_map(|command| {
+                    std::iter::once(command.get_name())
+                        .chain(command.get_all_aliases())
+                        .map(String::from)
+                        .collect::<Vec<_>>()
+                })
+                .collect();
+            cli::plugins::list_command(&builtins, &format)?;
+        }
+
+        Commands::External(command) => {
+            let global = cli::plugins::GlobalFlags {
+                verbose: cli.verbose,
+                debug: cli.debug,
+                quiet: cli.quiet,
+                no_color: cli.no_color,
+                read_only: cli.read_only,
+                timeout: cli.timeout,
+                cache_dir: cli.cache_dir,
+                jobs: cli.jobs,
+                machine_readable: cli.machine_readable,
+                plain: cli.plain,
+                lang: cli.lang,
+                os_root: cli.os_root,
+                extra_disks: cli.extra_disks,
+                mount_options: cli.mount_options,
+            };
+            let code = cli::plugins::run_plugin(&command, global)?;
+            if code != 0 {
+                std::process::exit(code);
+            }
+        }
     }
 
     Ok(())
@@ -0,0 +1,667 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Registry client for the OCI distribution API
+//!
+//! Handles the token handshake registries use (a 401 with a
+//! `WWW-Authenticate: Bearer realm=...` challenge, answered by fetching a
+//! token from the realm) as well as plain basic authentication.
+
+use super::{validate_digest, Descriptor, ImageIndex, INDEX_MEDIA_TYPE};
+use crate::core::{Error, Result};
+use base64::Engine;
+use reqwest::blocking::{Body, Client, RequestBuilder, Response};
+use reqwest::header::{
+    HeaderMap, ACCEPT, CONTENT_LENGTH, CONTENT_TYPE, LOCATION, WWW_AUTHENTICATE,
+};
+use reqwest::StatusCode;
+use serde_json::Value;
+use sha2::{Digest as _, Sha256};
+use std::collections::HashMap;
+use std::fmt;
+use std::io::{Read, Write};
+use std::path::PathBuf;
+
+/// Header carrying the digest of a manifest or blob
+const DIGEST_HEADER: &str = "Docker-Content-Digest";
+
+/// Header a registry sets when it indexed the subject of a pushed manifest
+const SUBJECT_HEADER: &str = "OCI-Subject";
+
+/// Registry credentials
+#[derive(Clone, PartialEq, Eq)]
+pub struct Credentials {
+    pub username: String,
+    pub password: String,
+}
+
+impl fmt::Debug for Credentials {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("Credentials")
+            .field("username", &self.username)
+            .field("password", &"[hidden]")
+            .finish()
+    }
+}
+
+impl Credentials {
+    pub fn new(username: impl Into<String>, password: impl Into<String>) -> Self {
+        Self {
+            username: username.into(),
+            password: password.into(),
+        }
+    }
+
+    /// Credentials for `registry` stored by `podman login` or `docker login`
+    ///
+    /// Looks at `$REGISTRY_AUTH_FILE`, the containers auth.json files and
+    /// `$DOCKER_CONFIG/config.json` (or `~/.docker/config.json`), in that
+    /// order. Credential helpers are not consulted.
+    pub fn from_auth_files(registry: &str) -> Option<Self> {
+        auth_files()
+            .into_iter()
+            .filter_map(|path| std::fs::read_to_string(path).ok())
+            .filter_map(|content| serde_json::from_str::<Value>(&content).ok())
+            .find_map(|config| lookup_auth(&config, registry))
+    }
+}
+
+/// Authentication in use for requests
+#[derive(Debug, Clone)]
+enum Auth {
+    Anonymous,
+    Basic,
+    Bearer(String),
+}
+
+/// Result of a manifest upload
+#[derive(Debug, Clone)]
+pub struct PushedManifest {
+    /// Digest of the manifest as stored
+    pub digest: String,
+    /// Whether the registry indexed the manifest's subject for the
+    /// referrers API (if not, the referrers tag has to be maintained)
+    pub subject_indexed: bool,
+}
+
+/// Client for one registry
+///
+/// # Examples
+///
+/// ```no_run
+/// use guestkit::oci::{Credentials, Reference, RegistryClient};
+///
+/// let reference: Reference = "quay.io/acme/rhel9:latest".parse()?;
+/// let mut client = RegistryClient::new(reference.api_host(), false)?
+///     .with_credentials(Credentials::from_auth_files(&reference.registry));
+/// let (manifest, _media_type, digest) =
+///     client.get_manifest(&reference.repository, reference.target())?;
+/// println!("{} ({} bytes)", digest, manifest.len());
+/// # Ok::<(), guestkit::Error>(())
+/// ```
+pub struct RegistryClient {
+    http: Client,
+    base_url: String,
+    credentials: Option<Credentials>,
+    auth: Auth,
+}
+
+impl RegistryClient {
+    /// Create a client for `host` (e.g. "quay.io" or "localhost:5000")
+    ///
+    /// `plain_http` talks HTTP instead of HTTPS, for local test registries.
+    pub fn new(host: &str, plain_http: bool) -> Result<Self> {
+        let http = Client::builder()
+            .user_agent(concat!("guestkit/", env!("CARGO_PKG_VERSION")))
+            // Disk images take a while to upload
+            .timeout(None)
+            .build()
+            .map_err(|e| Error::Registry(format!("Failed to create HTTP client: {}", e)))?;
+
+        Ok(Self {
+            http,
+            base_url: format!("{}://{}", if plain_http { "http" } else { "https" }, host),
+            credentials: None,
+            auth: Auth::Anonymous,
+        })
+    }
+
+    /// Authenticate with these credentials when the registry asks for it
+    pub fn with_credentials(mut self, credentials: Option<Credentials>) -> Self {
+        self.credentials = credentials;
+        self
+    }
+
+    /// Registry base URL
+    pub fn base_url(&self) -> &str {
+        &self.base_url
+    }
+
+    /// Whether the registry already has blob `digest`
+    pub fn blob_exists(&mut self, repository: &str, digest: &str) -> Result<bool> {
+        let url = format!("{}/v2/{}/blobs/{}", self.base_url, repository, digest);
+        let response = self.send(|http| http.head(&url))?;
+        match response.status() {
+            status if status.is_success() => Ok(true),
+            StatusCode::NOT_FOUND => Ok(false),
+            _ => Err(registry_error("Blob check", response)),
+        }
+    }
+
+    /// Upload a blob unless the registry already has it
+    ///
+    /// `open` produces the blob content; it is called again if the upload
+    /// has to be repeated after authenticating.
+    pub fn push_blob<F>(&mut self, repository: &str, digest: &str, size: u64, open: F) -> Result<()>
+    where
+        F: Fn() -> Result<Body>,
+    {
+        validate_digest(digest)?;
+        if self.blob_exists(repository, digest)? {
+            return Ok(());
+        }
+
+        let url = format!("{}/v2/{}/blobs/uploads/", self.base_url, repository);
+        let response = check(self.send(|http| http.post(&url))?, "Blob upload")?;
+        let location = response
+            .headers()
+            .get(LOCATION)
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| Error::Registry("Upload response has no Location".to_string()))?;
+        let location = if location.starts_with("http://") || location.starts_with("https://") {
+            location.to_string()
+        } else {
+            format!("{}{}", self.base_url, location)
+        };
+        let separator = if location.contains('?') { '&' } else { '?' };
+        let upload_url = format!("{}{}digest={}", location, separator, digest);
+
+        let mut body_error = None;
+        let response = self.send(|http| {
+            let request = http
+                .put(&upload_url)
+                .header(CONTENT_TYPE, "application/octet-stream")
+                .header(CONTENT_LENGTH, size);
+            match open() {
+                Ok(body) => request.body(body),
+                Err(e) => {
+                    body_error = Some(e);
+                    request
+                }
+            }
+        });
+        if let Some(e) = body_error {
+            return Err(e);
+        }
+        check(response?, "Blob upload")?;
+        Ok(())
+    }
+
+    /// Upload a small blob held in memory
+    pub fn push_blob_bytes(&mut self, repository: &str, data: &[u8]) -> Result<String> {
+        let digest = sha256_digest(data);
+        let data = data.to_vec();
+        self.push_blob(repository, &digest, data.len() as u64, || {
+            Ok(Body::from(data.clone()))
+        })?;
+        Ok(digest)
+    }
+
+    /// Upload a manifest under a tag or its digest
+    pub fn put_manifest(
+        &mut self,
+        repository: &str,
+        reference: &str,
+        media_type: &str,
+        manifest: &[u8],
+    ) -> Result<PushedManifest> {
+        let url = format!(
+            "{}/v2/{}/manifests/{}",
+            self.base_url, repository, reference
+        );
+        let response = self.send(|http| {
+            http.put(&url)
+                .header(CONTENT_TYPE, media_type)
+                .body(manifest.to_vec())
+        })?;
+        let response = check(response, "Manifest upload")?;
+
+        let digest =
+            header(response.headers(), DIGEST_HEADER).unwrap_or_else(|| sha256_digest(manifest));
+        Ok(PushedManifest {
+            digest,
+            subject_indexed: response.headers().contains_key(SUBJECT_HEADER),
+        })
+    }
+
+    /// Fetch a manifest by tag or digest
+    ///
+    /// Returns the raw manifest, its media type and its digest. Manifests
+    /// fetched by digest are verified against it.
+    pub fn get_manifest(
+        &mut self,
+        repository: &str,
+        reference: &str,
+    ) -> Result<(Vec<u8>, String, String)> {
+        let url = format!(
+            "{}/v2/{}/manifests/{}",
+            self.base_url, repository, reference
+        );
+        let accept = format!("{}, {}", super::MANIFEST_MEDIA_TYPE, INDEX_MEDIA_TYPE);
+        let response = self.send(|http| http.get(&url).header(ACCEPT, &accept))?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(Error::NotFound(format!(
+                "Manifest {} not found in {}",
+                reference, repository
+            )));
+        }
+        let response = check(response, "Manifest fetch")?;
+
+        let media_type = header(response.headers(), CONTENT_TYPE.as_str()).unwrap_or_default();
+        let body = response
+            .bytes()
+            .map_err(|e| Error::Registry(format!("Failed to read manifest: {}", e)))?
+            .to_vec();
+        let digest = sha256_digest(&body);
+        if reference.starts_with("sha256:") && reference != digest {
+            return Err(Error::Registry(format!(
+                "Manifest digest mismatch: expected {}, got {}",
+                reference, digest
+            )));
+        }
+
+        Ok((body, media_type, digest))
+    }
+
+    /// Digest of the manifest a tag points to
+    pub fn resolve_digest(&mut self, repository: &str, reference: &str) -> Result<String> {
+        if reference.starts_with("sha256:") {
+            return Ok(reference.to_string());
+        }
+        let url = format!(
+            "{}/v2/{}/manifests/{}",
+            self.base_url, repository, reference
+        );
+        let accept = format!("{}, {}", super::MANIFEST_MEDIA_TYPE, INDEX_MEDIA_TYPE);
+        let response = self.send(|http| http.head(&url).header(ACCEPT, &accept))?;
+        if response.status() == StatusCode::NOT_FOUND {
+            return Err(Error::NotFound(format!(
+                "Manifest {} not found in {}",
+                reference, repository
+            )));
+        }
+        let response = check(response, "Manifest check")?;
+        match header(response.headers(), DIGEST_HEADER) {
+            Some(digest) => Ok(digest),
+            // Not every registry sends the digest on HEAD
+            None => Ok(self.get_manifest(repository, reference)?.2),
+        }
+    }
+
+    /// Download a blob into `out`, verifying its digest
+    pub fn fetch_blob(
+        &mut self,
+        repository: &str,
+        digest: &str,
+        out: &mut dyn Write,
+    ) -> Result<u64> {
+        validate_digest(digest)?;
+        let url = format!("{}/v2/{}/blobs/{}", self.base_url, repository, digest);
+        let mut response = check(self.send(|http| http.get(&url))?, "Blob fetch")?;
+
+        let mut hasher = Sha256::new();
+        let mut buffer = vec![0u8; 1024 * 1024];
+        let mut size = 0u64;
+        loop {
+            let read = response
+                .read(&mut buffer)
+                .map_err(|e| Error::Registry(format!("Failed to download {}: {}", digest, e)))?;
+            if read == 0 {
+                break;
+            }
+            hasher.update(&buffer[..read]);
+            out.write_all(&buffer[..read])?;
+            size += read as u64;
+        }
+
+        let actual = format!("sha256:{:x}", hasher.finalize());
+        if actual != digest {
+            return Err(Error::Registry(format!(
+                "Blob digest mismatch: expected {}, got {}",
+                digest, actual
+            )));
+        }
+        Ok(size)
+    }
+
+    /// Manifests that refer to `digest` through their subject
+    ///
+    /// Uses the referrers API and falls back to the referrers tag
+    /// (`sha256-<hex>`) on registries without it.
+    pub fn referrers(&mut self, repository: &str, digest: &str) -> Result<Vec<Descriptor>> {
+        validate_digest(digest)?;
+        let url = format!("{}/v2/{}/referrers/{}", self.base_url, repository, digest);
+        let response = self.send(|http| http.get(&url).header(ACCEPT, INDEX_MEDIA_TYPE))?;
+
+        let index = if response.status() == StatusCode::NOT_FOUND {
+            self.referrers_tag_index(repository, digest)?
+        } else {
+            let body = check(response, "Referrers fetch")?
+                .bytes()
+                .map_err(|e| Error::Registry(format!("Failed to read referrers: {}", e)))?;
+            serde_json::from_slice(&body)
+                .map_err(|e| Error::Registry(format!("Invalid referrers index: {}", e)))?
+        };
+        Ok(index.manifests)
+    }
+
+    /// Index stored under the referrers tag of `digest` (empty if none)
+    pub fn referrers_tag_index(&mut self, repository: &str, digest: &str) -> Result<ImageIndex> {
+        match self.get_manifest(repository, &referrers_tag(digest)) {
+            Ok((body, _, _)) => serde_json::from_slice(&body)
+                .map_err(|e| Error::Registry(format!("Invalid referrers index: {}", e))),
+            Err(Error::NotFound(_)) => Ok(ImageIndex::default()),
+            Err(e) => Err(e),
+        }
+    }
+
+    /// Send a request, authenticating and retrying once on a 401
+    fn send<F>(&mut self, mut build: F) -> Result<Response>
+    where
+        F: FnMut(&Client) -> RequestBuilder,
+    {
+        let response = self
+            .authorize(build(&self.http))
+            .send()
+            .map_err(|e| self.http_error(e))?;
+        if response.status() != StatusCode::UNAUTHORIZED {
+            return Ok(response);
+        }
+
+        let challenge = header(response.headers(), WWW_AUTHENTICATE.as_str()).ok_or_else(|| {
+            Error::PermissionDenied(format!("{} refused the request", self.base_url))
+        })?;
+        self.authenticate(&challenge)?;
+
+        let response = self
+            .authorize(build(&self.http))
+            .send()
+            .map_err(|e| self.http_error(e))?;
+        if response.status() == StatusCode::UNAUTHORIZED {
+            return Err(Error::PermissionDenied(format!(
+                "{} rejected the credentials{}",
+                self.base_url,
+                if self.credentials.is_none() {
+                    " (none given; log in with podman/docker or pass --username)"
+                } else {
+                    ""
+                }
+            )));
+        }
+        Ok(response)
+    }
+
+    fn authorize(&self, request: RequestBuilder) -> RequestBuilder {
+        match (&self.auth, &self.credentials) {
+            (Auth::Bearer(token), _) => request.bearer_auth(token),
+            (Auth::Basic, Some(credentials)) => {
+                request.basic_auth(&credentials.username, Some(&credentials.password))
+            }
+            _ => request,
+        }
+    }
+
+    /// Answer an authentication challenge
+    fn authenticate(&mut self, challenge: &str) -> Result<()> {
+        let (scheme, params) = parse_challenge(challenge);
+
+        if scheme.eq_ignore_ascii_case("basic") {
+            if self.credentials.is_none() {
+                return Err(Error::PermissionDenied(format!(
+                    "{} requires credentials",
+                    self.base_url
+                )));
+            }
+            self.auth = Auth::Basic;
+            return Ok(());
+        }
+        if !scheme.eq_ignore_ascii_case("bearer") {
+            return Err(Error::Registry(format!(
+                "Unsupported authentication scheme: {}",
+                scheme
+            )));
+        }
+
+        let realm = params
+            .get("realm")
+            .ok_or_else(|| Error::Registry("Bearer challenge has no realm".to_string()))?;
+        let query: Vec<(&str, &str)> = ["service", "scope"]
+            .iter()
+            .filter_map(|key| params.get(*key).map(|value| (*key, value.as_str())))
+            .collect();
+
+        let mut request = self.http.get(realm.as_str()).query(&query);
+        if let Some(credentials) = &self.credentials {
+            request = request.basic_auth(&credentials.username, Some(&credentials.password));
+        }
+        let response = request.send().map_err(|e| self.http_error(e))?;
+        if response.status() == StatusCode::UNAUTHORIZED {
+            return Err(Error::PermissionDenied(format!(
+                "Token server {} rejected the credentials",
+                realm
+            )));
+        }
+        let body: Value = check(response, "Token request")?
+            .json_value()
+            .map_err(|e| Error::Registry(format!("Invalid token response: {}", e)))?;
+
+        let token = body["token"]
+            .as_str()
+            .or_else(|| body["access_token"].as_str())
+            .ok_or_else(|| Error::Registry("Token response has no token".to_string()))?;
+        self.auth = Auth::Bearer(token.to_string());
+        Ok(())
+    }
+
+    fn http_error(&self, error: reqwest::Error) -> Error {
+        Error::Registry(format!("Request to {} failed: {}", self.base_url, error))
+    }
+}
+
+/// JSON body of a response (without reqwest's json feature)
+trait JsonBody {
+    fn json_value(self) -> std::result::Result<Value, String>;
+}
+
+impl JsonBody for Response {
+    fn json_value(self) -> std::result::Result<Value, String> {
+        let body = self.bytes().map_err(|e| e.to_string())?;
+        serde_json::from_slice(&body).map_err(|e| e.to_string())
+    }
+}
+
+/// Tag holding the referrers index of `digest` on registries without the
+/// referrers API
+pub fn referrers_tag(digest: &str) -> String {
+    digest.replacen(':', "-", 1)
+}
+
+/// "sha256:<hex>" digest of `data`
+pub fn sha256_digest(data: &[u8]) -> String {
+    format!("sha256:{:x}", Sha256::digest(data))
+}
+
+/// Return the response if it succeeded, the registry's error otherwise
+fn check(response: Response, what: &str) -> Result<Response> {
+    if response.status().is_success() {
+        Ok(response)
+    } else {
+        Err(registry_error(what, response))
+    }
+}
+
+/// Error from a failed response, with the registry's error messages
+fn registry_error(what: &str, response: Response) -> Error {
+    let status = response.status();
+    let messages: Vec<String> = response
+        .json_value()
+        .ok()
+        .and_then(|body| body["errors"].as_array().cloned())
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|e| {
+            let code = e["code"].as_str()?;
+            Some(match e["message"].as_str() {
+                Some(message) => format!("{}: {}", code, message),
+                None => code.to_string(),
+            })
+        })
+        .collect();
+
+    let error = format!("{} failed: HTTP {}", what, status);
+    match status {
+        StatusCode::FORBIDDEN => Error::PermissionDenied(error),
+        _ if messages.is_empty() => Error::Registry(error),
+        _ => Error::Registry(format!("{} ({})", error, messages.join("; "))),
+    }
+}
+
+fn header(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get(name)
+        .and_then(|v| v.to_str().ok())
+        .map(str::to_string)
+}
+
+/// Split a `WWW-Authenticate` challenge into scheme and parameters
+fn parse_challenge(challenge: &str) -> (String, HashMap<String, String>) {
+    let challenge = challenge.trim();
+    let (scheme, rest) = challenge.split_once(' ').unwrap_or((challenge, ""));
+
+    let mut params = HashMap::new();
+    let mut chars = rest.chars().peekable();
+    loop {
+        let key: String = chars
+            .by_ref()
+            .skip_while(|c| *c == ',' || c.is_whitespace())
+            .take_while(|c| *c != '=')
+            .collect();
+        if key.is_empty() {
+            break;
+        }
+
+        let mut value = String::new();
+        if chars.peek() == Some(&'"') {
+            chars.next();
+            let mut escaped = false;
+            for c in chars.by_ref() {
+                match c {
+                    '\\' if !escaped => escaped = true,
+                    '"' if !escaped => break,
+                    _ => {
+                        value.push(c);
+                        escaped = false;
+                    }
+                }
+            }
+        } else {
+            value = chars.by_ref().take_while(|c| *c != ',').collect();
+        }
+        params.insert(key.trim().to_lowercase(), value.trim().to_string());
+    }
+
+    (scheme.to_string(), params)
+}
+
+/// Auth files written by podman and docker, most specific first
+fn auth_files() -> Vec<PathBuf> {
+    let mut files = Vec::new();
+    if let Some(path) = std::env::var_os("REGISTRY_AUTH_FILE") {
+        files.push(PathBuf::from(path));
+    }
+    if let Some(runtime) = std::env::var_os("XDG_RUNTIME_DIR") {
+        files.push(PathBuf::from(runtime).join("containers/auth.json"));
+    }
+    if let Some(config) = dirs::config_dir() {
+        files.push(config.join("containers/auth.json"));
+    }
+    match std::env::var_os("DOCKER_CONFIG") {
+        Some(dir) => files.push(PathBuf::from(dir).join("config.json")),
+        None => {
+            if let Some(home) = dirs::home_dir() {
+                files.push(home.join(".docker/config.json"));
+            }
+        }
+    }
+    files
+}
+
+/// Credentials for `registry` in a parsed auth file
+fn lookup_auth(config: &Value, registry: &str) -> Option<Credentials> {
+    let auths = config["auths"].as_object()?;
+    let mut keys = vec![registry.to_string(), format!("https://{}", registry)];
+    if registry == super::DEFAULT_REGISTRY {
+        keys.push("https://index.docker.io/v1/".to_string());
+        keys.push("index.docker.io".to_string());
+    }
+
+    let entry = keys.iter().find_map(|key| auths.get(key))?;
+    let decoded = base64::engine::general_purpose::STANDARD
+        .decode(entry["auth"].as_str()?)
+        .ok()?;
+    let decoded = String::from_utf8(decoded).ok()?;
+    let (username, password) = decoded.split_once(':')?;
+    Some(Credentials::new(username, password))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_challenge() {
+        let (scheme, params) = parse_challenge(
+            r#"Bearer realm="https://auth.example.com/token",service="registry.example.com",scope="repository:vms/web:pull,push""#,
+        );
+        assert_eq!(scheme, "Bearer");
+        assert_eq!(params["realm"], "https://auth.example.com/token");
+        assert_eq!(params["service"], "registry.example.com");
+        assert_eq!(params["scope"], "repository:vms/web:pull,push");
+
+        let (scheme, params) = parse_challenge(r#"Basic realm="Registry Realm""#);
+        assert_eq!(scheme, "Basic");
+        assert_eq!(params["realm"], "Registry Realm");
+    }
+
+    #[test]
+    fn test_lookup_auth() {
+        let config = serde_json::json!({
+            "auths": {
+                "quay.io": {"auth": base64::engine::general_purpose::STANDARD.encode("robot:s3cr:et")},
+                "https://index.docker.io/v1/": {"auth": base64::engine::general_purpose::STANDARD.encode("hub:pw")},
+            }
+        });
+
+        assert_eq!(
+            lookup_auth(&config, "quay.io"),
+            Some(Credentials::new("robot", "s3cr:et"))
+        );
+        assert_eq!(
+            lookup_auth(&config, "docker.io"),
+            Some(Credentials::new("hub", "pw"))
+        );
+        assert_eq!(lookup_auth(&config, "ghcr.io"), None);
+    }
+
+    #[test]
+    fn test_referrers_tag() {
+        assert_eq!(
+            referrers_tag(
+                "sha256:44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a"
+            ),
+            "sha256-44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a"
+        );
+        assert_eq!(
+            sha256_digest(b"{}"),
+            "sha256:44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a"
+        );
+    }
+}
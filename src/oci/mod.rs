@@ -0,0 +1,633 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Disk images as OCI artifacts
+//!
+//! A disk image is pushed as an OCI image manifest with artifact type
+//! `application/vnd.guestkit.disk.v1`, the empty config and a single layer
+//! holding the image file (`application/vnd.guestkit.disk.layer.v1.<format>`).
+//! SBOMs and inspection reports are pushed as separate manifests whose
+//! `subject` is the disk manifest, so they are found through the referrers
+//! API and can be signed like any other artifact.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use guestkit::oci::{self, PushOptions, Reference, RegistryClient};
+//! use std::path::Path;
+//!
+//! let reference: Reference = "localhost:5000/vms/web:v2".parse()?;
+//! let mut client = RegistryClient::new(reference.api_host(), false)?;
+//!
+//! let pushed = oci::push_disk(
+//!     &mut client,
+//!     &reference,
+//!     Path::new("web.qcow2"),
+//!     &PushOptions::default(),
+//! )?;
+//! println!("Pushed {}", pushed.reference);
+//!
+//! let pulled = oci::pull_disk(&mut client, &reference, Path::new("/tmp/web"), true)?;
+//! println!("Disk at {}", pulled.disk.display());
+//! # Ok::<(), guestkit::Error>(())
+//! ```
+
+pub mod client;
+pub mod reference;
+
+pub use client::{Credentials, PushedManifest, RegistryClient};
+pub use reference::{validate_digest, Reference, DEFAULT_REGISTRY, DEFAULT_TAG};
+
+use crate::core::audit::image_digest;
+use crate::core::{Error, Result};
+use crate::disk::DiskReader;
+use reqwest::blocking::Body;
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fs::File;
+use std::io::{BufWriter, Read};
+use std::path::{Path, PathBuf};
+
+/// OCI image manifest
+pub const MANIFEST_MEDIA_TYPE: &str = "application/vnd.oci.image.manifest.v1+json";
+
+/// OCI image index
+pub const INDEX_MEDIA_TYPE: &str = "application/vnd.oci.image.index.v1+json";
+
+/// Empty config blob (`{}`) used by artifacts without a config
+pub const EMPTY_MEDIA_TYPE: &str = "application/vnd.oci.empty.v1+json";
+pub const EMPTY_DIGEST: &str =
+    "sha256:44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a";
+const EMPTY_CONFIG: &[u8] = b"{}";
+
+/// Artifact type of disk image manifests
+pub const DISK_ARTIFACT_TYPE: &str = "application/vnd.guestkit.disk.v1";
+
+/// Media type of disk layers, followed by the image format
+pub const DISK_LAYER_MEDIA_TYPE_PREFIX: &str = "application/vnd.guestkit.disk.layer.v1.";
+
+/// Media types of attachments
+pub const SPDX_MEDIA_TYPE: &str = "application/spdx+json";
+pub const CYCLONEDX_MEDIA_TYPE: &str = "application/vnd.cyclonedx+json";
+pub const CYCLONEDX_XML_MEDIA_TYPE: &str = "application/vnd.cyclonedx+xml";
+pub const DSSE_MEDIA_TYPE: &str = "application/vnd.dsse.envelope.v1+json";
+pub const INSPECTION_MEDIA_TYPE: &str = "application/vnd.guestkit.inspection.v1+json";
+pub const OCTET_STREAM_MEDIA_TYPE: &str = "application/octet-stream";
+
+/// File name of a layer
+pub const ANNOTATION_TITLE: &str = "org.opencontainers.image.title";
+pub const ANNOTATION_CREATED: &str = "org.opencontainers.image.created";
+/// Format of the disk image in a disk layer
+pub const ANNOTATION_DISK_FORMAT: &str = "io.guestkit.disk.format";
+
+/// Content descriptor
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Descriptor {
+    pub media_type: String,
+    pub digest: String,
+    pub size: u64,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artifact_type: Option<String>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+}
+
+impl Descriptor {
+    pub fn new(media_type: impl Into<String>, digest: impl Into<String>, size: u64) -> Self {
+        Self {
+            media_type: media_type.into(),
+            digest: digest.into(),
+            size,
+            artifact_type: None,
+            annotations: BTreeMap::new(),
+        }
+    }
+
+    /// File name recorded in the title annotation
+    pub fn title(&self) -> Option<&str> {
+        self.annotations.get(ANNOTATION_TITLE).map(String::as_str)
+    }
+}
+
+/// OCI image manifest
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct Manifest {
+    pub schema_version: u32,
+    #[serde(default)]
+    pub media_type: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub artifact_type: Option<String>,
+    pub config: Descriptor,
+    #[serde(default)]
+    pub layers: Vec<Descriptor>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub subject: Option<Descriptor>,
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub annotations: BTreeMap<String, String>,
+}
+
+impl Manifest {
+    /// Manifest of an artifact with the empty config
+    pub fn artifact(artifact_type: &str, layers: Vec<Descriptor>) -> Self {
+        Self {
+            schema_version: 2,
+            media_type: MANIFEST_MEDIA_TYPE.to_string(),
+            artifact_type: Some(artifact_type.to_string()),
+            config: Descriptor::new(EMPTY_MEDIA_TYPE, EMPTY_DIGEST, EMPTY_CONFIG.len() as u64),
+            layers,
+            subject: None,
+            annotations: BTreeMap::new(),
+        }
+    }
+
+    /// Artifact type, falling back to the config media type as the spec
+    /// asks for manifests without one
+    pub fn artifact_type(&self) -> &str {
+        self.artifact_type
+            .as_deref()
+            .unwrap_or(&self.config.media_type)
+    }
+
+    /// The disk layer of a disk artifact
+    pub fn disk_layer(&self) -> Option<&Descriptor> {
+        self.layers
+            .iter()
+            .find(|layer| layer.media_type.starts_with(DISK_LAYER_MEDIA_TYPE_PREFIX))
+    }
+
+    fn to_bytes(&self) -> Result<Vec<u8>> {
+        serde_json::to_vec(self).map_err(|e| Error::Registry(format!("Invalid manifest: {}", e)))
+    }
+}
+
+/// OCI image index (also the format of referrers lists)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
+pub struct ImageIndex {
+    pub schema_version: u32,
+    #[serde(default)]
+    pub media_type: String,
+    #[serde(default)]
+    pub manifests: Vec<Descriptor>,
+}
+
+impl Default for ImageIndex {
+    fn default() -> Self {
+        Self {
+            schema_version: 2,
+            media_type: INDEX_MEDIA_TYPE.to_string(),
+            manifests: Vec::new(),
+        }
+    }
+}
+
+/// File to attach to a pushed disk image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Attachment {
+    pub path: PathBuf,
+    pub media_type: String,
+}
+
+impl Attachment {
+    /// Attachment with the media type inferred from its content
+    pub fn new(path: impl Into<PathBuf>) -> Result<Self> {
+        let path = path.into();
+        let media_type = infer_media_type(&path)?.to_string();
+        Ok(Self { path, media_type })
+    }
+
+    /// Attachment with an explicit media type
+    pub fn with_media_type(path: impl Into<PathBuf>, media_type: impl Into<String>) -> Self {
+        Self {
+            path: path.into(),
+            media_type: media_type.into(),
+        }
+    }
+}
+
+/// Options for [`push_disk`]
+#[derive(Debug, Clone, Default)]
+pub struct PushOptions {
+    /// Disk format (detected from the image if not set)
+    pub format: Option<String>,
+    /// Extra manifest annotations
+    pub annotations: BTreeMap<String, String>,
+    /// Files pushed as referrers of the disk image
+    pub attachments: Vec<Attachment>,
+}
+
+/// Result of [`push_disk`]
+#[derive(Debug, Clone, Serialize)]
+pub struct PushedArtifact {
+    /// Reference pinned to the manifest digest
+    #[serde(serialize_with = "serialize_display")]
+    pub reference: Reference,
+    /// Manifest digest
+    pub digest: String,
+    /// The disk layer
+    pub disk: Descriptor,
+    /// Manifest descriptors of the attachments
+    pub attachments: Vec<Descriptor>,
+}
+
+/// Result of [`pull_disk`]
+#[derive(Debug, Clone, Serialize)]
+pub struct PulledArtifact {
+    /// Manifest digest
+    pub digest: String,
+    /// Disk format from the layer media type
+    pub format: String,
+    /// Downloaded disk image
+    pub disk: PathBuf,
+    /// Downloaded attachments
+    pub attachments: Vec<PathBuf>,
+}
+
+/// Push a disk image and its attachments
+///
+/// The manifest is tagged with the reference's tag (`latest` if none).
+/// Blobs the registry already has are not uploaded again.
+pub fn push_disk(
+    client: &mut RegistryClient,
+    reference: &Reference,
+    disk: &Path,
+    options: &PushOptions,
+) -> Result<PushedArtifact> {
+    if reference.digest.is_some() {
+        return Err(Error::InputValidation(format!(
+            "Cannot push to a digest reference: {}",
+            reference
+        )));
+    }
+    let tag = reference.tag.as_deref().unwrap_or(DEFAULT_TAG);
+    let repository = &reference.repository;
+
+    let format = match &options.format {
+        Some(format) => format.to_lowercase(),
+        None => DiskReader::open(disk)?.format().as_str().to_string(),
+    };
+    let title = file_title(disk)?;
+
+    let size = std::fs::metadata(disk)?.len();
+    let digest = image_digest(disk)?;
+    let path = disk.to_path_buf();
+    client.push_blob(repository, &digest, size, || {
+        Ok(Body::sized(File::open(&path)?, size))
+    })?;
+
+    let mut layer = Descriptor::new(
+        format!("{}{}", DISK_LAYER_MEDIA_TYPE_PREFIX, format),
+        digest,
+        size,
+    );
+    layer
+        .annotations
+        .insert(ANNOTATION_TITLE.to_string(), title.clone());
+    layer
+        .annotations
+        .insert(ANNOTATION_DISK_FORMAT.to_string(), format);
+
+    client.push_blob_bytes(repository, EMPTY_CONFIG)?;
+
+    let mut manifest = Manifest::artifact(DISK_ARTIFACT_TYPE, vec![layer.clone()]);
+    manifest.annotations = options.annotations.clone();
+    manifest
+        .annotations
+        .entry(ANNOTATION_TITLE.to_string())
+        .or_insert(title);
+    manifest
+        .annotations
+        .entry(ANNOTATION_CREATED.to_string())
+        .or_insert_with(created_now);
+    let body = manifest.to_bytes()?;
+    let pushed = client.put_manifest(repository, tag, MANIFEST_MEDIA_TYPE, &body)?;
+
+    let mut subject = Descriptor::new(MANIFEST_MEDIA_TYPE, &pushed.digest, body.len() as u64);
+    subject.artifact_type = Some(DISK_ARTIFACT_TYPE.to_string());
+
+    let attachments = options
+        .attachments
+        .iter()
+        .map(|attachment| push_attachment(client, repository, &subject, attachment))
+        .collect::<Result<Vec<_>>>()?;
+
+    Ok(PushedArtifact {
+        reference: reference.with_digest(&pushed.digest),
+        digest: pushed.digest,
+        disk: layer,
+        attachments,
+    })
+}
+
+/// Push one attachment as a referrer of `subject`
+fn push_attachment(
+    client: &mut RegistryClient,
+    repository: &str,
+    subject: &Descriptor,
+    attachment: &Attachment,
+) -> Result<Descriptor> {
+    let data = std::fs::read(&attachment.path)?;
+    let digest = client.push_blob_bytes(repository, &data)?;
+
+    let mut layer = Descriptor::new(&attachment.media_type, digest, data.len() as u64);
+    layer
+        .annotations
+        .insert(ANNOTATION_TITLE.to_string(), file_title(&attachment.path)?);
+
+    let mut manifest = Manifest::artifact(&attachment.media_type, vec![layer]);
+    manifest.subject = Some(subject.clone());
+    manifest
+        .annotations
+        .insert(ANNOTATION_CREATED.to_string(), created_now());
+    let body = manifest.to_bytes()?;
+    let digest = client::sha256_digest(&body);
+    let pushed = client.put_manifest(repository, &digest, MANIFEST_MEDIA_TYPE, &body)?;
+
+    let mut descriptor = Descriptor::new(MANIFEST_MEDIA_TYPE, pushed.digest, body.len() as u64);
+    descriptor.artifact_type = Some(attachment.media_type.clone());
+    descriptor.annotations = manifest.annotations;
+
+    // Registries without the referrers API need the referrers tag updated
+    if !pushed.subject_indexed {
+        let tag = client::referrers_tag(&subject.digest);
+        let mut index = client.referrers_tag_index(repository, &subject.digest)?;
+        if !index
+            .manifests
+            .iter()
+            .any(|m| m.digest == descriptor.digest)
+        {
+            index.manifests.push(descriptor.clone());
+            let body = serde_json::to_vec(&index)
+                .map_err(|e| Error::Registry(format!("Invalid index: {}", e)))?;
+            client.put_manifest(repository, &tag, INDEX_MEDIA_TYPE, &body)?;
+        }
+    }
+
+    Ok(descriptor)
+}
+
+/// Fetch and parse the manifest of a disk artifact
+///
+/// Returns the manifest and its digest.
+pub fn fetch_disk_manifest(
+    client: &mut RegistryClient,
+    reference: &Reference,
+) -> Result<(Manifest, String)> {
+    let (body, media_type, digest) =
+        client.get_manifest(&reference.repository, reference.target())?;
+    if media_type.starts_with(INDEX_MEDIA_TYPE) {
+        return Err(Error::InvalidFormat(format!(
+            "{} is an image index, not a disk image",
+            reference
+        )));
+    }
+    let manifest: Manifest = serde_json::from_slice(&body)
+        .map_err(|e| Error::InvalidFormat(format!("Invalid manifest for {}: {}", reference, e)))?;
+    if manifest.disk_layer().is_none() {
+        return Err(Error::InvalidFormat(format!(
+            "{} is not a disk image (artifact type {})",
+            reference,
+            manifest.artifact_type()
+        )));
+    }
+    Ok((manifest, digest))
+}
+
+/// Pull a disk image, and optionally its attachments, into `dest_dir`
+///
+/// Files are downloaded under a `.part` name and renamed once their digest
+/// has been verified.
+pub fn pull_disk(
+    client: &mut RegistryClient,
+    reference: &Reference,
+    dest_dir: &Path,
+    attachments: bool,
+) -> Result<PulledArtifact> {
+    let (manifest, digest) = fetch_disk_manifest(client, reference)?;
+    let layer = manifest
+        .disk_layer()
+        .cloned()
+        .ok_or_else(|| Error::InvalidFormat(format!("{} has no disk layer", reference)))?;
+    let format = layer.media_type[DISK_LAYER_MEDIA_TYPE_PREFIX.len()..].to_string();
+
+    std::fs::create_dir_all(dest_dir)?;
+    let name = layer
+        .title()
+        .and_then(safe_file_name)
+        .unwrap_or_else(|| format!("disk.{}", format));
+    let disk = download_blob(client, &reference.repository, &layer, &dest_dir.join(name))?;
+
+    let mut files = Vec::new();
+    if attachments {
+        for referrer in client.referrers(&reference.repository, &digest)? {
+            let (body, _, _) = client.get_manifest(&reference.repository, &referrer.digest)?;
+            let Ok(attachment) = serde_json::from_slice::<Manifest>(&body) else {
+                continue;
+            };
+            for layer in &attachment.layers {
+                let name = layer
+                    .title()
+                    .and_then(safe_file_name)
+                    .unwrap_or_else(|| short_digest(&layer.digest).to_string());
+                files.push(download_blob(
+                    client,
+                    &reference.repository,
+                    layer,
+                    &dest_dir.join(name),
+                )?);
+            }
+        }
+    }
+
+    Ok(PulledArtifact {
+        digest,
+        format,
+        disk,
+        attachments: files,
+    })
+}
+
+/// Download a blob to `path` through a `.part` file
+fn download_blob(
+    client: &mut RegistryClient,
+    repository: &str,
+    descriptor: &Descriptor,
+    path: &Path,
+) -> Result<PathBuf> {
+    let mut partial = path.as_os_str().to_owned();
+    partial.push(".part");
+    let partial = PathBuf::from(partial);
+
+    let result = (|| {
+        let mut out = BufWriter::new(File::create(&partial)?);
+        let size = client.fetch_blob(repository, &descriptor.digest, &mut out)?;
+        out.into_inner().map_err(|e| e.into_error())?.sync_all()?;
+        if size != descriptor.size {
+            return Err(Error::Registry(format!(
+                "Size mismatch for {}: expected {} bytes, got {}",
+                descriptor.digest, descriptor.size, size
+            )));
+        }
+        std::fs::rename(&partial, path)?;
+        Ok(path.to_path_buf())
+    })();
+    if result.is_err() {
+        let _ = std::fs::remove_file(&partial);
+    }
+    result
+}
+
+/// Media type of an attachment, from its content
+///
+/// Recognizes SPDX and CycloneDX SBOMs, signed attestations and guestctl
+/// inspection reports; anything else is `application/octet-stream`.
+pub fn infer_media_type(path: &Path) -> Result<&'static str> {
+    let mut head = Vec::new();
+    File::open(path)?.take(64 * 1024).read_to_end(&mut head)?;
+
+    if head.starts_with(b"<?xml")
+        && String::from_utf8_lossy(&head).contains("http://cyclonedx.org/schema/bom/")
+    {
+        return Ok(CYCLONEDX_XML_MEDIA_TYPE);
+    }
+    let Some(json) = serde_json::from_slice::<serde_json::Value>(&head)
+        .ok()
+        .filter(serde_json::Value::is_object)
+    else {
+        return Ok(OCTET_STREAM_MEDIA_TYPE);
+    };
+    let spdx3 = json["@context"]
+        .as_str()
+        .is_some_and(|context| context.starts_with("https://spdx.org/rdf/3"));
+    Ok(if json.get("spdxVersion").is_some() || spdx3 {
+        SPDX_MEDIA_TYPE
+    } else if json["bomFormat"] == "CycloneDX" {
+        CYCLONEDX_MEDIA_TYPE
+    } else if json["payloadType"].is_string() && json["signatures"].is_array() {
+        DSSE_MEDIA_TYPE
+    } else if json["os"].is_object() {
+        INSPECTION_MEDIA_TYPE
+    } else {
+        OCTET_STREAM_MEDIA_TYPE
+    })
+}
+
+/// Creation timestamp annotation value
+fn created_now() -> String {
+    chrono::Utc::now().to_rfc3339_opts(chrono::SecondsFormat::Secs, true)
+}
+
+/// File name to record as the title of `path`
+fn file_title(path: &Path) -> Result<String> {
+    path.file_name()
+        .and_then(|name| name.to_str())
+        .map(str::to_string)
+        .ok_or_else(|| Error::PathValidation(format!("No file name in {}", path.display())))
+}
+
+/// A title usable as a file name (no directories, not hidden)
+fn safe_file_name(title: &str) -> Option<String> {
+    let name = Path::new(title).file_name()?.to_str()?;
+    (!name.starts_with('.')).then(|| name.to_string())
+}
+
+/// First 12 hex digits of a digest
+fn short_digest(digest: &str) -> &str {
+    let hex = digest.split_once(':').map_or(digest, |(_, hex)| hex);
+    &hex[..hex.len().min(12)]
+}
+
+fn serialize_display<T: std::fmt::Display, S: serde::Serializer>(
+    value: &T,
+    serializer: S,
+) -> std::result::Result<S::Ok, S::Error> {
+    serializer.collect_str(value)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::io::Write;
+
+    #[test]
+    fn test_artifact_manifest_json() {
+        let mut layer = Descriptor::new(
+            format!("{}qcow2", DISK_LAYER_MEDIA_TYPE_PREFIX),
+            EMPTY_DIGEST,
+            2,
+        );
+        layer
+            .annotations
+            .insert(ANNOTATION_TITLE.to_string(), "web.qcow2".to_string());
+        let manifest = Manifest::artifact(DISK_ARTIFACT_TYPE, vec![layer]);
+
+        let json: serde_json::Value =
+            serde_json::from_slice(&manifest.to_bytes().unwrap()).unwrap();
+        assert_eq!(json["schemaVersion"], 2);
+        assert_eq!(json["mediaType"], MANIFEST_MEDIA_TYPE);
+        assert_eq!(json["artifactType"], DISK_ARTIFACT_TYPE);
+        assert_eq!(json["config"]["mediaType"], EMPTY_MEDIA_TYPE);
+        assert_eq!(json["config"]["digest"], EMPTY_DIGEST);
+        assert_eq!(
+            json["layers"][0]["annotations"][ANNOTATION_TITLE],
+            "web.qcow2"
+        );
+        assert!(json.get("subject").is_none());
+
+        let parsed: Manifest = serde_json::from_value(json).unwrap();
+        assert_eq!(
+            parsed.disk_layer().and_then(Descriptor::title),
+            Some("web.qcow2")
+        );
+        assert_eq!(client::sha256_digest(EMPTY_CONFIG), EMPTY_DIGEST);
+    }
+
+    #[test]
+    fn test_infer_media_type() {
+        let dir = tempfile::tempdir().unwrap();
+        let cases = [
+            (
+                r#"{"spdxVersion": "SPDX-2.3", "packages": []}"#,
+                SPDX_MEDIA_TYPE,
+            ),
+            (
+                r#"{"@context": "https://spdx.org/rdf/3.0.1/spdx-context.jsonld", "@graph": []}"#,
+                SPDX_MEDIA_TYPE,
+            ),
+            (
+                r#"{"bomFormat": "CycloneDX", "specVersion": "1.6"}"#,
+                CYCLONEDX_MEDIA_TYPE,
+            ),
+            (
+                "<?xml version=\"1.0\"?>\n<bom xmlns=\"http://cyclonedx.org/schema/bom/1.6\">",
+                CYCLONEDX_XML_MEDIA_TYPE,
+            ),
+            (
+                r#"{"payloadType": "application/vnd.in-toto+json", "payload": "", "signatures": []}"#,
+                DSSE_MEDIA_TYPE,
+            ),
+            (r#"{"os": {"type": "linux"}}"#, INSPECTION_MEDIA_TYPE),
+            ("[1, 2]", OCTET_STREAM_MEDIA_TYPE),
+            ("plain text", OCTET_STREAM_MEDIA_TYPE),
+        ];
+        for (i, (content, expected)) in cases.iter().enumerate() {
+            let path = dir.path().join(format!("attachment-{}", i));
+            File::create(&path)
+                .unwrap()
+                .write_all(content.as_bytes())
+                .unwrap();
+            assert_eq!(infer_media_type(&path).unwrap(), *expected, "{}", content);
+        }
+    }
+
+    #[test]
+    fn test_safe_file_name() {
+        assert_eq!(safe_file_name("web.qcow2"), Some("web.qcow2".to_string()));
+        assert_eq!(
+            safe_file_name("../../etc/passwd"),
+            Some("passwd".to_string())
+        );
+        assert_eq!(safe_file_name(".bashrc"), None);
+        assert_eq!(safe_file_name(".."), None);
+    }
+}
@@ -0,0 +1,207 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! OCI artifact references (`registry/repository[:tag][@digest]`)
+
+use crate::core::{Error, Result};
+use std::fmt;
+use std::str::FromStr;
+
+/// Registry assumed when a reference names none
+pub const DEFAULT_REGISTRY: &str = "docker.io";
+
+/// Tag assumed when a reference has neither tag nor digest
+pub const DEFAULT_TAG: &str = "latest";
+
+/// Reference to an artifact in a registry
+///
+/// # Examples
+///
+/// ```
+/// use guestkit::oci::Reference;
+///
+/// let reference: Reference = "quay.io/acme/rhel9:2024-06".parse()?;
+/// assert_eq!(reference.registry, "quay.io");
+/// assert_eq!(reference.repository, "acme/rhel9");
+/// assert_eq!(reference.tag.as_deref(), Some("2024-06"));
+/// # Ok::<(), guestkit::Error>(())
+/// ```
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Reference {
+    /// Registry host, with port if any (e.g. "localhost:5000")
+    pub registry: String,
+    /// Repository path (e.g. "acme/rhel9")
+    pub repository: String,
+    pub tag: Option<String>,
+    /// Manifest digest (e.g. "sha256:...")
+    pub digest: Option<String>,
+}
+
+impl Reference {
+    /// Tag or digest to fetch the manifest by
+    pub fn target(&self) -> &str {
+        self.digest
+            .as_deref()
+            .or(self.tag.as_deref())
+            .unwrap_or(DEFAULT_TAG)
+    }
+
+    /// Same repository, pinned to `digest`
+    pub fn with_digest(&self, digest: impl Into<String>) -> Self {
+        Self {
+            digest: Some(digest.into()),
+            ..self.clone()
+        }
+    }
+
+    /// Host to connect to (Docker Hub serves docker.io from another host)
+    pub fn api_host(&self) -> &str {
+        if self.registry == DEFAULT_REGISTRY {
+            "registry-1.docker.io"
+        } else {
+            &self.registry
+        }
+    }
+}
+
+impl FromStr for Reference {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let invalid = |reason: &str| {
+            Error::InputValidation(format!("Invalid reference '{}': {}", value, reason))
+        };
+
+        let (rest, digest) = match value.split_once('@') {
+            Some((rest, digest)) => {
+                validate_digest(digest).map_err(|_| invalid("malformed digest"))?;
+                (rest, Some(digest.to_string()))
+            }
+            None => (value, None),
+        };
+
+        // A colon after the last slash starts the tag; one before it is a port
+        let (name, tag) = match rest.rfind(':') {
+            Some(colon) if !rest[colon..].contains('/') => {
+                (&rest[..colon], Some(rest[colon + 1..].to_string()))
+            }
+            _ => (rest, None),
+        };
+
+        // The first component is a registry if it looks like a host
+        let (registry, repository) = match name.split_once('/') {
+            Some((host, path))
+                if host.contains('.') || host.contains(':') || host == "localhost" =>
+            {
+                (host.to_string(), path.to_string())
+            }
+            _ => (DEFAULT_REGISTRY.to_string(), name.to_string()),
+        };
+        let repository = if registry == DEFAULT_REGISTRY && !repository.contains('/') {
+            format!("library/{}", repository)
+        } else {
+            repository
+        };
+
+        if repository.is_empty()
+            || !repository.split('/').all(|component| {
+                !component.is_empty()
+                    && component
+                        .chars()
+                        .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || "._-".contains(c))
+            })
+        {
+            return Err(invalid(
+                "repository must be lowercase letters, digits and . _ - separated by /",
+            ));
+        }
+        if let Some(tag) = &tag {
+            if tag.is_empty()
+                || tag.len() > 128
+                || tag.starts_with(['.', '-'])
+                || !tag
+                    .chars()
+                    .all(|c| c.is_ascii_alphanumeric() || "._-".contains(c))
+            {
+                return Err(invalid("malformed tag"));
+            }
+        }
+
+        Ok(Self {
+            registry,
+            repository,
+            tag,
+            digest,
+        })
+    }
+}
+
+impl fmt::Display for Reference {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}", self.registry, self.repository)?;
+        if let Some(tag) = &self.tag {
+            write!(f, ":{}", tag)?;
+        }
+        if let Some(digest) = &self.digest {
+            write!(f, "@{}", digest)?;
+        }
+        Ok(())
+    }
+}
+
+/// Check that `digest` is a sha256 digest ("sha256:" and 64 hex digits)
+pub fn validate_digest(digest: &str) -> Result<()> {
+    let valid = digest.strip_prefix("sha256:").is_some_and(|hex| {
+        hex.len() == 64
+            && hex
+                .chars()
+                .all(|c| c.is_ascii_digit() || ('a'..='f').contains(&c))
+    });
+    if valid {
+        Ok(())
+    } else {
+        Err(Error::InputValidation(format!(
+            "Unsupported digest: {}",
+            digest
+        )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const DIGEST: &str = "sha256:44136fa355b3678a1146ad16f7e8649e94fb4fc21fe77e8310c060f61caaff8a";
+
+    #[test]
+    fn test_parse_reference() {
+        let r: Reference = "localhost:5000/vms/web:v2".parse().unwrap();
+        assert_eq!(r.registry, "localhost:5000");
+        assert_eq!(r.repository, "vms/web");
+        assert_eq!(r.tag.as_deref(), Some("v2"));
+        assert_eq!(r.target(), "v2");
+        assert_eq!(r.to_string(), "localhost:5000/vms/web:v2");
+
+        let r: Reference = "localhost:5000/vms/web".parse().unwrap();
+        assert_eq!(r.tag, None);
+        assert_eq!(r.target(), DEFAULT_TAG);
+
+        let r: Reference = format!("quay.io/acme/rhel9@{}", DIGEST).parse().unwrap();
+        assert_eq!(r.digest.as_deref(), Some(DIGEST));
+        assert_eq!(r.target(), DIGEST);
+
+        let r: Reference = "fedora:40".parse().unwrap();
+        assert_eq!(r.registry, DEFAULT_REGISTRY);
+        assert_eq!(r.repository, "library/fedora");
+        assert_eq!(r.api_host(), "registry-1.docker.io");
+    }
+
+    #[test]
+    fn test_parse_invalid_reference() {
+        assert!("quay.io/Acme/rhel9".parse::<Reference>().is_err());
+        assert!("quay.io/acme//rhel9".parse::<Reference>().is_err());
+        assert!("quay.io/acme/rhel9:".parse::<Reference>().is_err());
+        assert!("quay.io/acme/rhel9@sha256:1234"
+            .parse::<Reference>()
+            .is_err());
+        assert!("quay.io/".parse::<Reference>().is_err());
+    }
+}
@@ -0,0 +1,980 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Plan application - executes fix plans with safety checks
+
+use super::types::*;
+use crate::core::escape::shell_quote;
+use crate::lint::{self, FileKind};
+use crate::Guestfs;
+use anyhow::{Context, Result};
+use base64::Engine;
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+
+/// Rollback manifest written to the backup directory
+pub const ROLLBACK_FILE: &str = "rollback.json";
+
+/// Applies fix plans to VM disks
+pub struct PlanApplicator {
+    vm_path: String,
+    dry_run: bool,
+    backup_dir: Option<PathBuf>,
+}
+
+impl PlanApplicator {
+    /// Create a new plan applicator
+    pub fn new(vm_path: String, dry_run: bool) -> Self {
+        Self {
+            vm_path,
+            dry_run,
+            backup_dir: None,
+        }
+    }
+
+    /// Write a rollback manifest to this directory when applying
+    pub fn with_backup_dir(mut self, backup_dir: impl Into<PathBuf>) -> Self {
+        self.backup_dir = Some(backup_dir.into());
+        self
+    }
+
+    /// Apply a fix plan
+    pub fn apply(&self, plan: &FixPlan) -> Result<ApplyResult> {
+        self.apply_with_progress(plan, |_, _| true)
+    }
+
+    /// Apply a fix plan, calling `progress` before each operation
+    ///
+    /// `progress` receives the number of operations started so far and the
+    /// next operation. Returning false stops the run; remaining operations
+    /// are reported as skipped.
+    pub fn apply_with_progress<F>(&self, plan: &FixPlan, mut progress: F) -> Result<ApplyResult>
+    where
+        F: FnMut(usize, &Operation) -> bool,
+    {
+        if self.dry_run {
+            return Ok(ApplyResult {
+                success: true,
+                operations_applied: 0,
+                operations_failed: 0,
+                operations_skipped: plan.operations.len(),
+                message: "Dry run completed - no changes made".to_string(),
+                operations: plan
+                    .operations
+                    .iter()
+                    .map(|op| OperationResult::new(op, OperationOutcome::Skipped, "dry run"))
+                    .collect(),
+                post_apply: plan.post_apply.clone(),
+                rollback_file: None,
+            });
+        }
+
+        let validation = self.validate(plan)?;
+        if !validation.valid {
+            anyhow::bail!("Invalid plan: {}", validation.errors.join("; "));
+        }
+        let order = execution_order(plan)?;
+
+        // Mount the guest read-write
+        let mut g = Guestfs::new()?;
+        g.add_drive_opts(&self.vm_path, false, None)?;
+        g.launch()?;
+
+        let roots = g.inspect_os()?;
+        if roots.is_empty() {
+            anyhow::bail!("No operating systems found in disk image");
+        }
+        let root = &roots[0];
+        let package_format = g.inspect_get_package_format(root).unwrap_or_default();
+
+        let mountpoints = g.inspect_get_mountpoints(root)?;
+        for (mp, dev) in mountpoints {
+            g.mount(&dev, &mp)
+                .with_context(|| format!("Failed to mount {} on {}", dev, mp))?;
+        }
+
+        let mut results = Vec::new();
+        // Operations whose dependents must not run
+        let mut blocked: HashSet<&str> = HashSet::new();
+        let mut stopped = false;
+
+        for (started, &index) in order.iter().enumerate() {
+            let op = &plan.operations[index];
+
+            if stopped {
+                results.push(OperationResult::new(
+                    op,
+                    OperationOutcome::Skipped,
+                    "stopped",
+                ));
+                continue;
+            }
+            if let Some(dep) = op.depends_on.iter().find(|d| blocked.contains(d.as_str())) {
+                blocked.insert(op.id.as_str());
+                results.push(OperationResult::new(
+                    op,
+                    OperationOutcome::Skipped,
+                    format!("dependency {} did not complete", dep),
+                ));
+                continue;
+            }
+            if !progress(started, op) {
+                stopped = true;
+                results.push(OperationResult::new(
+                    op,
+                    OperationOutcome::Skipped,
+                    "stopped",
+                ));
+                continue;
+            }
+
+            let mut rollback = Vec::new();
+            let result = match apply_operation(&mut g, op, &package_format, &mut rollback) {
+                Ok(Applied::Done(message)) => match op.validation {
+                    Some(ref check) => match run_validation(&mut g, check) {
+                        Ok(()) => OperationResult::new(op, OperationOutcome::Applied, message),
+                        Err(e) => OperationResult::new(
+                            op,
+                            OperationOutcome::Failed,
+                            format!("validation failed: {}", e),
+                        ),
+                    },
+                    None => OperationResult::new(op, OperationOutcome::Applied, message),
+                },
+                Ok(Applied::Unsupported(message)) => {
+                    OperationResult::new(op, OperationOutcome::Skipped, message)
+                }
+                Err(e) => OperationResult::new(op, OperationOutcome::Failed, format!("{:#}", e)),
+            };
+
+            if result.outcome != OperationOutcome::Applied {
+                blocked.insert(op.id.as_str());
+            }
+            // Keep rollback steps for partial changes made before a failure
+            results.push(OperationResult { rollback, ..result });
+        }
+
+        g.sync()?;
+        g.shutdown()?;
+
+        let count = |outcome| results.iter().filter(|r| r.outcome == outcome).count();
+        let applied = count(OperationOutcome::Applied);
+        let failed = count(OperationOutcome::Failed);
+        let skipped = count(OperationOutcome::Skipped);
+
+        let rollback_file = match self.backup_dir {
+            Some(ref dir) => Some(self.write_rollback(dir, plan, &results)?),
+            None => None,
+        };
+
+        let message = if failed == 0 && !stopped {
+            format!("Applied {} operations ({} skipped)", applied, skipped)
+        } else if stopped {
+            format!("Stopped after {} operations ({} failed)", applied, failed)
+        } else {
+            format!("{} of {} operations failed", failed, results.len())
+        };
+
+        Ok(ApplyResult {
+            success: failed == 0 && !stopped,
+            operations_applied: applied,
+            operations_failed: failed,
+            operations_skipped: skipped,
+            message,
+            operations: results,
+            post_apply: plan.post_apply.clone(),
+            rollback_file: rollback_file.map(|p| p.to_string_lossy().to_string()),
+        })
+    }
+
+    /// Validate a plan before applying
+    pub fn validate(&self, plan: &FixPlan) -> Result<ValidationResult> {
+        let mut errors = Vec::new();
+        let mut warnings = Vec::new();
+
+        // Check VM exists
+        if !Path::new(&self.vm_path).exists() {
+            errors.push(format!("VM disk not found: {}", self.vm_path));
+        }
+
+        // Check for circular dependencies
+        if self.has_circular_dependencies(plan) {
+            errors.push("Plan contains circular dependencies".to_string());
+        }
+
+        // Check for missing dependencies
+        for op in &plan.operations {
+            for dep_id in &op.depends_on {
+                if !plan.operations.iter().any(|o| &o.id == dep_id) {
+                    errors.push(format!(
+                        "Operation {} depends on non-existent operation {}",
+                        op.id, dep_id
+                    ));
+                }
+            }
+        }
+
+        // Warn about non-reversible operations
+        let non_reversible: Vec<&str> = plan
+            .operations
+            .iter()
+            .filter(|op| !op.reversible)
+            .map(|op| op.id.as_str())
+            .collect();
+
+        if !non_reversible.is_empty() {
+            warnings.push(format!(
+                "Non-reversible operations: {}",
+                non_reversible.join(", ")
+            ));
+        }
+
+        // Warn about operations that cannot be applied offline
+        for op in &plan.operations {
+            if matches!(op.op_type, OperationType::RegistryEdit(_)) {
+                warnings.push(format!("Registry edit {} will be skipped", op.id));
+            }
+        }
+
+        Ok(ValidationResult {
+            valid: errors.is_empty(),
+            errors,
+            warnings,
+        })
+    }
+
+    /// Check for circular dependencies
+    fn has_circular_dependencies(&self, plan: &FixPlan) -> bool {
+        execution_order(plan).is_err()
+    }
+
+    /// Write the rollback manifest for applied operations
+    fn write_rollback(
+        &self,
+        dir: &Path,
+        plan: &FixPlan,
+        results: &[OperationResult],
+    ) -> Result<PathBuf> {
+        std::fs::create_dir_all(dir)
+            .with_context(|| format!("Failed to create backup directory {}", dir.display()))?;
+
+        let manifest = RollbackManifest {
+            vm: self.vm_path.clone(),
+            profile: plan.profile.clone(),
+            created: Utc::now(),
+            operations: results
+                .iter()
+                .filter(|r| !r.rollback.is_empty())
+                .map(|r| RollbackEntry {
+                    id: r.id.clone(),
+                    steps: r.rollback.clone(),
+                })
+                .collect(),
+        };
+
+        let path = dir.join(ROLLBACK_FILE);
+        std::fs::write(&path, serde_json::to_string_pretty(&manifest)?)
+            .with_context(|| format!("Failed to write {}", path.display()))?;
+        Ok(path)
+    }
+
+    /// Rollback to a previous state
+    ///
+    /// Reads the rollback manifest from `backup_path` (a backup directory or
+    /// the manifest itself) and undoes operations in reverse order.
+    pub fn rollback(&self, backup_path: &str) -> Result<()> {
+        let path = Path::new(backup_path);
+        let manifest_path = if path.is_dir() {
+            path.join(ROLLBACK_FILE)
+        } else {
+            path.to_path_buf()
+        };
+
+        let content = std::fs::read_to_string(&manifest_path)
+            .with_context(|| format!("Failed to read {}", manifest_path.display()))?;
+        let manifest: RollbackManifest = serde_json::from_str(&content)
+            .with_context(|| format!("Invalid rollback manifest {}", manifest_path.display()))?;
+
+        if self.dry_run {
+            return Ok(());
+        }
+
+        let mut g = Guestfs::new()?;
+        g.add_drive_opts(&self.vm_path, false, None)?;
+        g.launch()?;
+
+        let roots = g.inspect_os()?;
+        if roots.is_empty() {
+            anyhow::bail!("No operating systems found in disk image");
+        }
+        let mountpoints = g.inspect_get_mountpoints(&roots[0])?;
+        for (mp, dev) in mountpoints {
+            g.mount(&dev, &mp)
+                .with_context(|| format!("Failed to mount {} on {}", dev, mp))?;
+        }
+
+        let mut errors = Vec::new();
+        for entry in manifest.operations.iter().rev() {
+            for step in entry.steps.iter().rev() {
+                if let Err(e) = undo_step(&mut g, step) {
+                    errors.push(format!("{}: {:#}", entry.id, e));
+                }
+            }
+        }
+
+        g.sync()?;
+        g.shutdown()?;
+
+        if !errors.is_empty() {
+            anyhow::bail!("Rollback incomplete:\n{}", errors.join("\n"));
+        }
+        Ok(())
+    }
+}
+
+/// Result of applying a plan
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ApplyResult {
+    pub success: bool,
+    pub operations_applied: usize,
+    pub operations_failed: usize,
+    pub operations_skipped: usize,
+    pub message: String,
+    /// Per-operation results in execution order
+    pub operations: Vec<OperationResult>,
+    /// Actions left to the operator (service restarts, reboots)
+    pub post_apply: Vec<PostApplyAction>,
+    /// Rollback manifest, when a backup directory was set
+    pub rollback_file: Option<String>,
+}
+
+/// Outcome of a single operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum OperationOutcome {
+    Applied,
+    Skipped,
+    Failed,
+}
+
+/// Result of applying a single operation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OperationResult {
+    pub id: String,
+    pub description: String,
+    pub outcome: OperationOutcome,
+    pub message: String,
+    /// Steps that undo this operation, applied in reverse order
+    #[serde(default)]
+    pub rollback: Vec<RollbackArtifact>,
+}
+
+impl OperationResult {
+    fn new(op: &Operation, outcome: OperationOutcome, message: impl Into<String>) -> Self {
+        Self {
+            id: op.id.clone(),
+            description: op.description.clone(),
+            outcome,
+            message: message.into(),
+            rollback: Vec::new(),
+        }
+    }
+}
+
+/// A single step undoing part of an operation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum RollbackArtifact {
+    /// Restore a file's previous content (base64) and mode
+    RestoreFile {
+        path: String,
+        content: String,
+        mode: u32,
+    },
+    /// Remove a file or empty directory created by the plan
+    Remove { path: String },
+    /// Restore previous mode and ownership
+    RestorePermissions {
+        path: String,
+        mode: u32,
+        uid: u32,
+        gid: u32,
+    },
+    /// Run a command in the guest
+    Command { command: String },
+}
+
+/// Rollback manifest for an applied plan
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollbackManifest {
+    pub vm: String,
+    pub profile: String,
+    pub created: DateTime<Utc>,
+    pub operations: Vec<RollbackEntry>,
+}
+
+/// Rollback steps for one operation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RollbackEntry {
+    pub id: String,
+    pub steps: Vec<RollbackArtifact>,
+}
+
+/// Result of validating a plan
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationResult {
+    pub valid: bool,
+    pub errors: Vec<String>,
+    pub warnings: Vec<String>,
+}
+
+enum Applied {
+    Done(String),
+    Unsupported(String),
+}
+
+/// Order operations so dependencies run first, keeping plan order otherwise
+pub fn execution_order(plan: &FixPlan) -> Result<Vec<usize>> {
+    let index: HashMap<&str, usize> = plan
+        .operations
+        .iter()
+        .enumerate()
+        .map(|(i, op)| (op.id.as_str(), i))
+        .collect();
+
+    let mut order = Vec::with_capacity(plan.operations.len());
+    let mut done = vec![false; plan.operations.len()];
+
+    while order.len() < plan.operations.len() {
+        let next = (0..plan.operations.len()).find(|&i| {
+            !done[i]
+                && plan.operations[i]
+                    .depends_on
+                    .iter()
+                    .all(|dep| index.get(dep.as_str()).map(|&d| done[d]).unwrap_or(true))
+        });
+
+        match next {
+            Some(i) => {
+                done[i] = true;
+                order.push(i);
+            }
+            None => {
+                let cycle: Vec<&str> = (0..plan.operations.len())
+                    .filter(|&i| !done[i])
+                    .map(|i| plan.operations[i].id.as_str())
+                    .collect();
+                anyhow::bail!("Circular dependencies between: {}", cycle.join(", "));
+            }
+        }
+    }
+
+    Ok(order)
+}
+
+fn apply_operation(
+    g: &mut Guestfs,
+    op: &Operation,
+    package_format: &str,
+    rollback: &mut Vec<RollbackArtifact>,
+) -> Result<Applied> {
+    // Explicit undo commands from the plan run after the built-in steps
+    if let Some(UndoInfo::Command { ref command }) = op.undo {
+        rollback.push(RollbackArtifact::Command {
+            command: command.clone(),
+        });
+    }
+
+    match &op.op_type {
+        OperationType::FileEdit(edit) => {
+            let original = g.read_file(&edit.file)?;
+            let text = String::from_utf8(original.clone())
+                .with_context(|| format!("{} is not a text file", edit.file))?;
+            let updated = apply_file_changes(&text, &edit.changes)?;
+            if updated == text {
+                return Ok(Applied::Done("already applied".to_string()));
+            }
+            check_edit_syntax(&edit.file, &text, &updated)?;
+
+            if edit.backup {
+                rollback.push(restore_file(g, &edit.file, &original)?);
+            }
+            g.write(&edit.file, updated.as_bytes())?;
+            Ok(Applied::Done(format!("edited {}", edit.file)))
+        }
+
+        OperationType::PackageInstall(install) => {
+            let missing: Vec<&str> = install
+                .packages
+                .iter()
+                .map(String::as_str)
+                .filter(|pkg| !package_installed(g, package_format, pkg))
+                .collect();
+            if missing.is_empty() {
+                return Ok(Applied::Done("already installed".to_string()));
+            }
+
+            let packages = missing
+                .iter()
+                .map(|p| shell_quote(p))
+                .collect::<Vec<_>>()
+                .join(" ");
+            let (install_cmd, remove_cmd) = match package_format {
+                "deb" => (
+                    format!(
+                        "DEBIAN_FRONTEND=noninteractive apt-get install -y {}",
+                        packages
+                    ),
+                    format!(
+                        "DEBIAN_FRONTEND=noninteractive apt-get remove -y {}",
+                        packages
+                    ),
+                ),
+                "rpm" => (
+                    format!("dnf -y install {0} || yum -y install {0}", packages),
+                    format!("dnf -y remove {0} || yum -y remove {0}", packages),
+                ),
+                other => {
+                    return Ok(Applied::Unsupported(format!(
+                        "package installation not supported for {} guests",
+                        if other.is_empty() { "unknown" } else { other }
+                    )))
+                }
+            };
+
+            g.sh(&install_cmd)?;
+            rollback.push(RollbackArtifact::Command {
+                command: remove_cmd,
+            });
+            Ok(Applied::Done(format!("installed {}", missing.join(", "))))
+        }
+
+        OperationType::ServiceOperation(service) => {
+            let mut done = Vec::new();
+            if let Some(ref state) = service.state {
+                let verb = match state.as_str() {
+                    "enabled" | "enable" => "enable",
+                    "disabled" | "disable" => "disable",
+                    "masked" | "mask" => "mask",
+                    other => anyhow::bail!("Unknown service state: {}", other),
+                };
+                let unit = shell_quote(&service.service);
+                let previous = g.sh(&format!("systemctl is-enabled {} 2>/dev/null; true", unit))?;
+                let previous = previous.trim();
+
+                g.sh(&format!("systemctl {} {}", verb, unit))?;
+                done.push(format!("{} {}", verb, service.service));
+
+                let undo = match previous {
+                    "enabled" => Some("enable"),
+                    "disabled" => Some("disable"),
+                    "masked" => Some("unmask"),
+                    _ => None,
+                };
+                if let Some(undo) = undo.filter(|u| *u != verb) {
+                    rollback.push(RollbackArtifact::Command {
+                        command: format!("systemctl {} {}", undo, unit),
+                    });
+                }
+            }
+            // Services cannot run in an offline guest
+            if service.start || service.restart {
+                done.push("start deferred to next boot".to_string());
+            }
+            Ok(Applied::Done(done.join("; ")))
+        }
+
+        OperationType::SelinuxMode(selinux) => {
+            let original = g.read_file(&selinux.file)?;
+            let text = String::from_utf8_lossy(&original);
+            let updated = set_config_value(&text, "SELINUX", &selinux.target);
+            if updated == text {
+                return Ok(Applied::Done("already applied".to_string()));
+            }
+
+            rollback.push(restore_file(g, &selinux.file, &original)?);
+            g.write(&selinux.file, updated.as_bytes())?;
+            Ok(Applied::Done(format!("SELinux set to {}", selinux.target)))
+        }
+
+        OperationType::RegistryEdit(_) => Ok(Applied::Unsupported(
+            "registry edits are not supported by offline application".to_string(),
+        )),
+
+        OperationType::CommandExec(exec) => {
+            let command = match exec.timeout {
+                Some(secs) => format!("timeout {} sh -c {}", secs, shell_quote(&exec.command)),
+                None => exec.command.clone(),
+            };
+            let (status, _) = run_with_status(g, &command)?;
+            if status != exec.expected_exit {
+                anyhow::bail!("exit code {}, expected {}", status, exec.expected_exit);
+            }
+            Ok(Applied::Done(format!("ran {}", exec.command)))
+        }
+
+        OperationType::FileCopy(copy) => {
+            if g.exists(&copy.destination)? {
+                if copy.backup {
+                    let original = g.read_file(&copy.destination)?;
+                    rollback.push(restore_file(g, &copy.destination, &original)?);
+                }
+            } else {
+                rollback.push(RollbackArtifact::Remove {
+                    path: copy.destination.clone(),
+                });
+            }
+            g.cp(&copy.source, &copy.destination)?;
+            Ok(Applied::Done(format!(
+                "copied {} to {}",
+                copy.source, copy.destination
+            )))
+        }
+
+        OperationType::DirectoryCreate(dir) => {
+            if g.is_dir(&dir.path)? {
+                return Ok(Applied::Done("already exists".to_string()));
+            }
+            g.mkdir_p(&dir.path)?;
+            rollback.push(RollbackArtifact::Remove {
+                path: dir.path.clone(),
+            });
+            if let Some(ref mode) = dir.mode {
+                g.chmod(parse_mode(mode)?, &dir.path)?;
+            }
+            Ok(Applied::Done(format!("created {}", dir.path)))
+        }
+
+        OperationType::FilePermissions(perms) => {
+            let stat = g.stat(&perms.path)?;
+            rollback.push(RollbackArtifact::RestorePermissions {
+                path: perms.path.clone(),
+                mode: stat.mode & 0o7777,
+                uid: stat.uid,
+                gid: stat.gid,
+            });
+
+            g.chmod(parse_mode(&perms.mode)?, &perms.path)?;
+            // chown inside the guest so user and group names resolve there
+            let owner = match (&perms.owner, &perms.group) {
+                (Some(owner), Some(group)) => Some(format!("{}:{}", owner, group)),
+                (Some(owner), None) => Some(owner.clone()),
+                (None, Some(group)) => Some(format!(":{}", group)),
+                (None, None) => None,
+            };
+            if let Some(owner) = owner {
+                g.sh(&format!(
+                    "chown {} {}",
+                    shell_quote(&owner),
+                    shell_quote(&perms.path)
+                ))?;
+            }
+            Ok(Applied::Done(format!(
+                "set {} on {}",
+                perms.mode, perms.path
+            )))
+        }
+    }
+}
+
+/// Apply line changes to file content
+///
+/// A change matches its `before` text at `line` (1-indexed) or, when that
+/// line differs or `line` is 0, at the first matching line. Changes whose
+/// `after` text is already present are treated as applied.
+pub fn apply_file_changes(content: &str, changes: &[FileChange]) -> Result<String> {
+    let trailing_newline = content.ends_with('\n');
+    let mut lines: Vec<String> = content.lines().map(str::to_string).collect();
+
+    for change in changes {
+        let at_line = change.line.checked_sub(1).filter(|&i| {
+            lines
+                .get(i)
+                .map(|l| l.trim() == change.before.trim())
+                .unwrap_or(false)
+        });
+        let found = at_line.or_else(|| lines.iter().position(|l| l.trim() == change.before.trim()));
+
+        match found {
+            Some(i) if !change.before.trim().is_empty() => lines[i] = change.after.clone(),
+            _ if lines.iter().any(|l| l.trim() == change.after.trim()) => {}
+            // An empty `before` appends a new line
+            _ if change.before.is_empty() => lines.push(change.after.clone()),
+            _ => anyhow::bail!("line not found: {}", change.before),
+        }
+    }
+
+    let mut output = lines.join("\n");
+    if trailing_newline || content.is_empty() {
+        output.push('\n');
+    }
+    Ok(output)
+}
+
+/// Set `KEY=value` in a shell-style config file, appending it if absent
+fn set_config_value(content: &str, key: &str, value: &str) -> String {
+    let prefix = format!("{}=", key);
+    let mut found = false;
+    let mut lines: Vec<String> = content
+        .lines()
+        .map(|line| {
+            if line.trim_start().starts_with(&prefix) {
+                found = true;
+                format!("{}{}", prefix, value)
+            } else {
+                line.to_string()
+            }
+        })
+        .collect();
+    if !found {
+        lines.push(format!("{}{}", prefix, value));
+    }
+
+    let mut output = lines.join("\n");
+    output.push('\n');
+    output
+}
+
+fn restore_file(g: &mut Guestfs, path: &str, original: &[u8]) -> Result<RollbackArtifact> {
+    let mode = g.stat(path).map(|s| s.mode & 0o7777).unwrap_or(0o644);
+    Ok(RollbackArtifact::RestoreFile {
+        path: path.to_string(),
+        content: base64::engine::general_purpose::STANDARD.encode(original),
+        mode,
+    })
+}
+
+fn undo_step(g: &mut Guestfs, step: &RollbackArtifact) -> Result<()> {
+    match step {
+        RollbackArtifact::RestoreFile {
+            path,
+            content,
+            mode,
+        } => {
+            let content = base64::engine::general_purpose::STANDARD
+                .decode(content)
+                .context("Invalid backup content")?;
+            g.write(path, &content)?;
+            g.chmod(*mode as i32, path)?;
+        }
+        RollbackArtifact::Remove { path } => {
+            if g.is_dir(path)? {
+                g.rmdir(path)?;
+            } else if g.exists(path)? {
+                g.rm(path)?;
+            }
+        }
+        RollbackArtifact::RestorePermissions {
+            path,
+            mode,
+            uid,
+            gid,
+        } => {
+            g.chmod(*mode as i32, path)?;
+            g.chown(*uid as i32, *gid as i32, path)?;
+        }
+        RollbackArtifact::Command { command } => {
+            g.sh(command)?;
+        }
+    }
+    Ok(())
+}
+
+/// Refuse an edit that introduces syntax errors into a known config format
+///
+/// Errors the file already had are not held against the edit.
+fn check_edit_syntax(file: &str, before: &str, after: &str) -> Result<()> {
+    let Some(kind) = FileKind::detect(file) else {
+        return Ok(());
+    };
+
+    let errors = lint::new_errors(kind, before, after);
+    if !errors.is_empty() {
+        let details: Vec<String> = errors.iter().map(|e| e.to_string()).collect();
+        anyhow::bail!(
+            "Edit would leave {} ({}) invalid: {}",
+            file,
+            kind,
+            details.join("; ")
+        );
+    }
+    Ok(())
+}
+
+fn run_validation(g: &mut Guestfs, check: &ValidationCheck) -> Result<()> {
+    let (status, output) = run_with_status(g, &check.command)?;
+    if status != check.expected_exit {
+        anyhow::bail!("exit code {}, expected {}", status, check.expected_exit);
+    }
+    if let Some(ref expected) = check.expected_output {
+        if !output.contains(expected.as_str()) {
+            anyhow::bail!("output does not contain {:?}", expected);
+        }
+    }
+    Ok(())
+}
+
+/// Run a shell command in the guest, returning its exit code and output
+fn run_with_status(g: &mut Guestfs, command: &str) -> Result<(i32, String)> {
+    // Report the exit code on the last line so non-zero exits are not errors
+    let output = g.sh(&format!("( {}\n)\nprintf '\\n%d' $?", command))?;
+    let (output, status) = output.rsplit_once('\n').unwrap_or(("", output.as_str()));
+    let status = status.trim().parse().context("Missing exit status")?;
+    Ok((status, output.to_string()))
+}
+
+fn package_installed(g: &mut Guestfs, package_format: &str, package: &str) -> bool {
+    let query = match package_format {
+        "deb" => format!("dpkg-query -W -f '${{Status}}' {}", shell_quote(package)),
+        "rpm" => format!("rpm -q {}", shell_quote(package)),
+        _ => return false,
+    };
+    match run_with_status(g, &format!("{} >/dev/null 2>&1", query)) {
+        Ok((status, _)) => status == 0,
+        Err(_) => false,
+    }
+}
+
+fn parse_mode(mode: &str) -> Result<i32> {
+    i32::from_str_radix(mode.trim_start_matches("0o"), 8)
+        .with_context(|| format!("Invalid mode: {}", mode))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn operation(id: &str, depends_on: &[&str]) -> Operation {
+        Operation {
+            id: id.to_string(),
+            op_type: OperationType::DirectoryCreate(DirectoryCreate {
+                path: format!("/opt/{}", id),
+                mode: None,
+            }),
+            priority: Priority::Medium,
+            description: id.to_string(),
+            risk: "low".to_string(),
+            reversible: true,
+            depends_on: depends_on.iter().map(|d| d.to_string()).collect(),
+            validation: None,
+            undo: None,
+        }
+    }
+
+    fn change(line: usize, before: &str, after: &str) -> FileChange {
+        FileChange {
+            line,
+            before: before.to_string(),
+            after: after.to_string(),
+            context: None,
+        }
+    }
+
+    #[test]
+    fn test_applicator_creation() {
+        let applicator = PlanApplicator::new("test.qcow2".to_string(), true);
+        assert_eq!(applicator.vm_path, "test.qcow2");
+        assert!(applicator.dry_run);
+    }
+
+    #[test]
+    fn test_dry_run() {
+        let applicator = PlanApplicator::new("test.qcow2".to_string(), true);
+        let plan = FixPlan::new("test.qcow2".to_string(), "security".to_string());
+        let result = applicator.apply(&plan).unwrap();
+        assert!(result.success);
+        assert_eq!(result.operations_applied, 0);
+    }
+
+    #[test]
+    fn test_execution_order() {
+        let mut plan = FixPlan::new("test.qcow2".to_string(), "security".to_string());
+        plan.add_operation(operation("restart", &["edit"]));
+        plan.add_operation(operation("edit", &[]));
+        plan.add_operation(operation("audit", &[]));
+        assert_eq!(execution_order(&plan).unwrap(), vec![1, 0, 2]);
+
+        plan.add_operation(operation("a", &["b"]));
+        plan.add_operation(operation("b", &["a"]));
+        assert!(execution_order(&plan).is_err());
+
+        let applicator = PlanApplicator::new("test.qcow2".to_string(), true);
+        let validation = applicator.validate(&plan).unwrap();
+        assert!(validation.errors.iter().any(|e| e.contains("circular")));
+    }
+
+    #[test]
+    fn test_apply_file_changes() {
+        let content = "Port 22\nPermitRootLogin yes\nUsePAM yes\n";
+
+        // Line 0 is located at apply time
+        let updated = apply_file_changes(
+            content,
+            &[change(0, "PermitRootLogin yes", "PermitRootLogin no")],
+        )
+        .unwrap();
+        assert_eq!(updated, "Port 22\nPermitRootLogin no\nUsePAM yes\n");
+
+        // Re-applying is a no-op
+        let again = apply_file_changes(
+            &updated,
+            &[change(2, "PermitRootLogin yes", "PermitRootLogin no")],
+        )
+        .unwrap();
+        assert_eq!(again, updated);
+
+        let appended = apply_file_changes(content, &[change(4, "", "MaxAuthTries 3")]).unwrap();
+        assert!(appended.ends_with("UsePAM yes\nMaxAuthTries 3\n"));
+
+        assert!(apply_file_changes(content, &[change(1, "Port 2222", "Port 22022")]).is_err());
+    }
+
+    #[test]
+    fn test_check_edit_syntax() {
+        let fstab = "UUID=abcd / ext4 defaults 0 1\n";
+        assert!(check_edit_syntax(
+            "/etc/fstab",
+            fstab,
+            "UUID=abcd / ext4 defaults,noatime 0 1\n"
+        )
+        .is_ok());
+        assert!(check_edit_syntax(
+            "/etc/fstab",
+            fstab,
+            "UUID=abcd / ext4 defaults 0 1\n/dev/sdb1 /data\n"
+        )
+        .is_err());
+
+        // Unknown formats are not checked
+        assert!(check_edit_syntax("/etc/hostname", "a\n", "{{{\n").is_ok());
+    }
+
+    #[test]
+    fn test_set_config_value() {
+        let config = "# comment\nSELINUX=permissive\nSELINUXTYPE=targeted\n";
+        assert_eq!(
+            set_config_value(config, "SELINUX", "enforcing"),
+            "# comment\nSELINUX=enforcing\nSELINUXTYPE=targeted\n"
+        );
+        assert_eq!(
+            set_config_value("", "SELINUX", "enforcing"),
+            "SELINUX=enforcing\n"
+        );
+    }
+
+    #[test]
+    fn test_rollback_artifact_serialization() {
+        let artifact = RollbackArtifact::RestorePermissions {
+            path: "/etc/shadow".to_string(),
+            mode: 0o640,
+            uid: 0,
+            gid: 42,
+        };
+        let json = serde_json::to_value(&artifact).unwrap();
+        assert_eq!(json["type"], "restore_permissions");
+        assert_eq!(
+            serde_json::from_value::<RollbackArtifact>(json).unwrap(),
+            artifact
+        );
+    }
+}
@@ -0,0 +1,14 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Fix plan types and application
+//!
+//! Fix plans are generated and previewed by `guestctl plan`; the types and
+//! the applicator live in the library so the worker can apply plans too.
+
+pub mod apply;
+pub mod types;
+
+pub use apply::{
+    ApplyResult, OperationOutcome, OperationResult, PlanApplicator, RollbackArtifact,
+    ValidationResult,
+};
+pub use types::{FixPlan, Operation, OperationType, PostApplyAction, Priority};
@@ -0,0 +1,455 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! OpenStack Glance publisher
+//!
+//! Authenticates against Keystone v3 with the `OS_*` environment variables
+//! `openstack` uses (password, application credential or token), finds the
+//! image endpoint in the service catalog, creates the image with properties
+//! derived from inspection and uploads the data.
+
+use super::{api_error, parse_query, request_error, DiskBus, ImageProperties, Published};
+use crate::core::{Error, Result};
+use reqwest::blocking::{Body, Client};
+use serde_json::{json, Map, Value};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::path::Path;
+use std::str::FromStr;
+use std::time::{Duration, Instant};
+
+/// Disk formats Glance accepts
+const DISK_FORMATS: [&str; 6] = ["qcow2", "raw", "vmdk", "vdi", "vhd", "vhdx"];
+
+/// Image visibilities Glance accepts
+const VISIBILITIES: [&str; 4] = ["private", "shared", "community", "public"];
+
+/// How long to wait for Glance to finish processing an upload
+const ACTIVE_TIMEOUT: Duration = Duration::from_secs(600);
+
+/// Glance image to create
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct GlanceTarget {
+    /// Image name
+    pub name: String,
+    /// Image visibility (Glance default if not set)
+    pub visibility: Option<String>,
+    /// Image endpoint, instead of looking it up in the service catalog
+    pub endpoint: Option<String>,
+    /// Extra image properties, overriding derived ones
+    pub properties: BTreeMap<String, String>,
+}
+
+impl FromStr for GlanceTarget {
+    type Err = Error;
+
+    /// Parse `NAME[?visibility=...&endpoint=...&KEY=VALUE...]`
+    fn from_str(value: &str) -> Result<Self> {
+        let (name, query) = value.split_once('?').unwrap_or((value, ""));
+        let name = super::percent_decode(name)?;
+        if name.is_empty() {
+            return Err(Error::InputValidation(
+                "Glance target needs an image name (glance://NAME)".to_string(),
+            ));
+        }
+
+        let mut target = Self {
+            name,
+            visibility: None,
+            endpoint: None,
+            properties: BTreeMap::new(),
+        };
+        for (key, value) in parse_query(query)? {
+            match key.as_str() {
+                "visibility" if VISIBILITIES.contains(&value.as_str()) => {
+                    target.visibility = Some(value)
+                }
+                "visibility" => {
+                    return Err(Error::InputValidation(format!(
+                        "Invalid visibility '{}' (expected {})",
+                        value,
+                        VISIBILITIES.join(", ")
+                    )));
+                }
+                "endpoint" => target.endpoint = Some(value.trim_end_matches('/').to_string()),
+                _ => {
+                    target.properties.insert(key, value);
+                }
+            }
+        }
+        Ok(target)
+    }
+}
+
+impl fmt::Display for GlanceTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.name)?;
+        let mut query: Vec<String> = Vec::new();
+        if let Some(visibility) = &self.visibility {
+            query.push(format!("visibility={}", visibility));
+        }
+        if let Some(endpoint) = &self.endpoint {
+            query.push(format!("endpoint={}", endpoint));
+        }
+        query.extend(self.properties.iter().map(|(k, v)| format!("{}={}", k, v)));
+        if !query.is_empty() {
+            write!(f, "?{}", query.join("&"))?;
+        }
+        Ok(())
+    }
+}
+
+impl GlanceTarget {
+    /// Create the image and upload `image` into it
+    pub fn publish(
+        &self,
+        image: &Path,
+        format: &str,
+        properties: &ImageProperties,
+    ) -> Result<Published> {
+        if !DISK_FORMATS.contains(&format) {
+            return Err(Error::Unsupported(format!(
+                "Glance does not accept {} images (expected {})",
+                format,
+                DISK_FORMATS.join(", ")
+            )));
+        }
+
+        let http = Client::builder()
+            .timeout(None)
+            .build()
+            .map_err(|e| Error::Publish(format!("Failed to create HTTP client: {}", e)))?;
+        let session = Session::from_env(&http)?;
+        let endpoint = match &self.endpoint {
+            Some(endpoint) => endpoint.clone(),
+            None => session.image_endpoint()?,
+        };
+
+        let mut image_properties = glance_properties(properties);
+        image_properties.extend(self.properties.clone());
+
+        let mut request = Map::new();
+        request.insert("name".to_string(), json!(self.name));
+        request.insert("disk_format".to_string(), json!(format));
+        request.insert("container_format".to_string(), json!("bare"));
+        if let Some(visibility) = &self.visibility {
+            request.insert("visibility".to_string(), json!(visibility));
+        }
+        for (key, value) in &image_properties {
+            request.insert(key.clone(), json!(value));
+        }
+
+        let response = http
+            .post(format!("{}/v2/images", endpoint))
+            .header("X-Auth-Token", &session.token)
+            .header("Content-Type", "application/json")
+            .body(Value::Object(request).to_string())
+            .send()
+            .map_err(|e| request_error(&endpoint, e))?;
+        if !response.status().is_success() {
+            return Err(api_error("Glance image creation", response));
+        }
+        let created: Value = response
+            .text()
+            .ok()
+            .and_then(|body| serde_json::from_str(&body).ok())
+            .unwrap_or_default();
+        let id = created["id"]
+            .as_str()
+            .ok_or_else(|| Error::Publish("Glance returned no image id".to_string()))?
+            .to_string();
+
+        let upload = self.upload(&http, &session, &endpoint, &id, image);
+        if let Err(e) = upload {
+            // Don't leave a queued image without data behind
+            let _ = http
+                .delete(format!("{}/v2/images/{}", endpoint, id))
+                .header("X-Auth-Token", &session.token)
+                .send();
+            return Err(e);
+        }
+
+        Ok(Published {
+            target: format!("glance://{}", self),
+            id,
+            vmid: None,
+            properties: image_properties,
+        })
+    }
+
+    /// Upload the image data and wait for the image to become active
+    fn upload(
+        &self,
+        http: &Client,
+        session: &Session,
+        endpoint: &str,
+        id: &str,
+        image: &Path,
+    ) -> Result<()> {
+        let size = std::fs::metadata(image)?.len();
+        let response = http
+            .put(format!("{}/v2/images/{}/file", endpoint, id))
+            .header("X-Auth-Token", &session.token)
+            .header("Content-Type", "application/octet-stream")
+            .body(Body::sized(File::open(image)?, size))
+            .send()
+            .map_err(|e| request_error(endpoint, e))?;
+        if !response.status().is_success() {
+            return Err(api_error("Glance upload", response));
+        }
+
+        let started = Instant::now();
+        loop {
+            let response = http
+                .get(format!("{}/v2/images/{}", endpoint, id))
+                .header("X-Auth-Token", &session.token)
+                .send()
+                .map_err(|e| request_error(endpoint, e))?;
+            if !response.status().is_success() {
+                return Err(api_error("Glance image status", response));
+            }
+            let status: Value =
+                serde_json::from_str(&response.text().unwrap_or_default()).unwrap_or_default();
+            match status["status"].as_str() {
+                Some("active") => return Ok(()),
+                Some("killed" | "deleted" | "deactivated") => {
+                    return Err(Error::Publish(format!(
+                        "Glance image {} is {}",
+                        id, status["status"]
+                    )));
+                }
+                _ if started.elapsed() > ACTIVE_TIMEOUT => {
+                    return Err(Error::Publish(format!(
+                        "Glance image {} did not become active",
+                        id
+                    )));
+                }
+                _ => std::thread::sleep(Duration::from_secs(2)),
+            }
+        }
+    }
+}
+
+/// Glance image properties for inspection results
+///
+/// Uses the property names Nova reads when booting an image.
+pub fn glance_properties(properties: &ImageProperties) -> BTreeMap<String, String> {
+    let mut result = BTreeMap::new();
+    if let Some(os_type) = &properties.os_type {
+        result.insert("os_type".to_string(), os_type.clone());
+    }
+    if let Some(distro) = &properties.distro {
+        // Inspection names mostly match the os_distro registry already
+        let distro = match distro.as_str() {
+            "archlinux" => "arch",
+            "redhat-based" => "rhel",
+            other => other,
+        };
+        result.insert("os_distro".to_string(), distro.to_string());
+    }
+    if let Some(version) = &properties.version {
+        result.insert("os_version".to_string(), version.clone());
+    }
+    if let Some(architecture) = &properties.architecture {
+        result.insert("architecture".to_string(), architecture.clone());
+    }
+    match properties.disk_bus {
+        Some(DiskBus::Virtio) => {
+            result.insert("hw_disk_bus".to_string(), "virtio".to_string());
+        }
+        Some(DiskBus::VirtioScsi) => {
+            result.insert("hw_disk_bus".to_string(), "scsi".to_string());
+            result.insert("hw_scsi_model".to_string(), "virtio-scsi".to_string());
+        }
+        Some(DiskBus::Sata) => {
+            result.insert("hw_disk_bus".to_string(), "sata".to_string());
+        }
+        Some(DiskBus::Ide) => {
+            result.insert("hw_disk_bus".to_string(), "ide".to_string());
+        }
+        None => {}
+    }
+    if properties.uefi {
+        result.insert("hw_firmware_type".to_string(), "uefi".to_string());
+        result.insert("hw_machine_type".to_string(), "q35".to_string());
+    }
+    result
+}
+
+/// Keystone token and service catalog
+struct Session {
+    token: String,
+    catalog: Value,
+}
+
+impl Session {
+    /// Authenticate with the `OS_*` environment variables
+    fn from_env(http: &Client) -> Result<Self> {
+        let auth_url = env("OS_AUTH_URL").ok_or_else(|| {
+            Error::Config("OS_AUTH_URL is not set (source your OpenStack RC file)".to_string())
+        })?;
+        let auth_url = auth_url.trim_end_matches('/');
+        let auth_url = if auth_url.ends_with("/v3") {
+            auth_url.to_string()
+        } else {
+            format!("{}/v3", auth_url)
+        };
+
+        let response = http
+            .post(format!("{}/auth/tokens", auth_url))
+            .header("Content-Type", "application/json")
+            .body(auth_request()?.to_string())
+            .send()
+            .map_err(|e| request_error(&auth_url, e))?;
+        if !response.status().is_success() {
+            return Err(api_error("Keystone authentication", response));
+        }
+
+        let token = response
+            .headers()
+            .get("X-Subject-Token")
+            .and_then(|v| v.to_str().ok())
+            .ok_or_else(|| Error::Publish("Keystone returned no token".to_string()))?
+            .to_string();
+        let body: Value = serde_json::from_str(&response.text().unwrap_or_default())
+            .map_err(|e| Error::Publish(format!("Invalid Keystone response: {}", e)))?;
+
+        Ok(Self {
+            token,
+            catalog: body["token"]["catalog"].clone(),
+        })
+    }
+
+    /// Image service endpoint for `OS_INTERFACE` (public) in `OS_REGION_NAME`
+    fn image_endpoint(&self) -> Result<String> {
+        let interface = env("OS_INTERFACE").unwrap_or_else(|| "public".to_string());
+        let interface = interface.trim_end_matches("URL");
+        let region = env("OS_REGION_NAME");
+
+        self.catalog
+            .as_array()
+            .into_iter()
+            .flatten()
+            .filter(|service| service["type"] == "image")
+            .flat_map(|service| service["endpoints"].as_array().into_iter().flatten())
+            .find(|endpoint| {
+                endpoint["interface"] == interface
+                    && region.as_deref().is_none_or(|region| {
+                        endpoint["region_id"] == region || endpoint["region"] == region
+                    })
+            })
+            .and_then(|endpoint| endpoint["url"].as_str())
+            .map(|url| url.trim_end_matches('/').to_string())
+            .ok_or_else(|| {
+                Error::Publish(format!(
+                    "No {} image endpoint in the service catalog{}",
+                    interface,
+                    region
+                        .map(|r| format!(" for region {}", r))
+                        .unwrap_or_default()
+                ))
+            })
+    }
+}
+
+/// Keystone v3 authentication request from the environment
+fn auth_request() -> Result<Value> {
+    if let (Some(id), Some(secret)) = (
+        env("OS_APPLICATION_CREDENTIAL_ID"),
+        env("OS_APPLICATION_CREDENTIAL_SECRET"),
+    ) {
+        return Ok(json!({"auth": {"identity": {
+            "methods": ["application_credential"],
+            "application_credential": {"id": id, "secret": secret},
+        }}}));
+    }
+
+    let identity = match (env("OS_TOKEN"), env("OS_USERNAME"), env("OS_PASSWORD")) {
+        (Some(token), _, _) => json!({"methods": ["token"], "token": {"id": token}}),
+        (None, Some(username), Some(password)) => {
+            let user_domain = env("OS_USER_DOMAIN_NAME").unwrap_or_else(|| "Default".to_string());
+            json!({"methods": ["password"], "password": {"user": {
+                "name": username,
+                "password": password,
+                "domain": {"name": user_domain},
+            }}})
+        }
+        _ => {
+            return Err(Error::Config(
+                "Set OS_USERNAME/OS_PASSWORD, OS_APPLICATION_CREDENTIAL_ID/SECRET or OS_TOKEN"
+                    .to_string(),
+            ));
+        }
+    };
+
+    let project_domain = env("OS_PROJECT_DOMAIN_NAME").unwrap_or_else(|| "Default".to_string());
+    let scope = match (env("OS_PROJECT_ID"), env("OS_PROJECT_NAME")) {
+        (Some(id), _) => json!({"project": {"id": id}}),
+        (None, Some(name)) => {
+            json!({"project": {"name": name, "domain": {"name": project_domain}}})
+        }
+        (None, None) => {
+            return Err(Error::Config(
+                "Set OS_PROJECT_NAME or OS_PROJECT_ID".to_string(),
+            ));
+        }
+    };
+
+    Ok(json!({"auth": {"identity": identity, "scope": scope}}))
+}
+
+/// Non-empty environment variable
+fn env(name: &str) -> Option<String> {
+    std::env::var(name).ok().filter(|value| !value.is_empty())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_glance_target() {
+        let target: GlanceTarget =
+            "web%2001?visibility=shared&hw_qemu_guest_agent=yes&endpoint=https://glance:9292/"
+                .parse()
+                .unwrap();
+        assert_eq!(target.name, "web 01");
+        assert_eq!(target.visibility.as_deref(), Some("shared"));
+        assert_eq!(target.endpoint.as_deref(), Some("https://glance:9292"));
+        assert_eq!(target.properties["hw_qemu_guest_agent"], "yes");
+
+        assert!("".parse::<GlanceTarget>().is_err());
+        assert!("web?visibility=everyone".parse::<GlanceTarget>().is_err());
+    }
+
+    #[test]
+    fn test_glance_properties() {
+        let windows = ImageProperties {
+            os_type: Some("windows".to_string()),
+            distro: Some("windows".to_string()),
+            version: Some("10.0".to_string()),
+            architecture: Some("x86_64".to_string()),
+            disk_bus: Some(DiskBus::VirtioScsi),
+            uefi: true,
+            ..Default::default()
+        };
+        let properties = glance_properties(&windows);
+        assert_eq!(properties["os_type"], "windows");
+        assert_eq!(properties["os_distro"], "windows");
+        assert_eq!(properties["os_version"], "10.0");
+        assert_eq!(properties["hw_disk_bus"], "scsi");
+        assert_eq!(properties["hw_scsi_model"], "virtio-scsi");
+        assert_eq!(properties["hw_firmware_type"], "uefi");
+
+        let arch = ImageProperties {
+            distro: Some("archlinux".to_string()),
+            disk_bus: Some(DiskBus::Virtio),
+            ..Default::default()
+        };
+        let properties = glance_properties(&arch);
+        assert_eq!(properties["os_distro"], "arch");
+        assert_eq!(properties["hw_disk_bus"], "virtio");
+        assert!(!properties.contains_key("hw_firmware_type"));
+
+        assert!(glance_properties(&ImageProperties::default()).is_empty());
+    }
+}
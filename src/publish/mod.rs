@@ -0,0 +1,219 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Publish converted images to OpenStack Glance and Proxmox VE
+//!
+//! Targets are written as URIs:
+//!
+//! - `glance://NAME[?visibility=...&KEY=VALUE...]` - create a Glance image
+//!   called NAME; extra query parameters become image properties. The
+//!   cloud comes from the usual `OS_*` environment variables.
+//! - `proxmox://HOST[:PORT]/NODE/STORAGE[?vmid=N&name=...&vm-storage=...]` -
+//!   upload to a storage of a Proxmox VE node for import, optionally creating
+//!   VM `vmid` from it. Credentials come from `PVE_API_TOKEN` or
+//!   `PVE_USER`/`PVE_PASSWORD`.
+//!
+//! Properties that make the image boot correctly (OS type, disk bus,
+//! firmware) are derived by inspecting it first.
+//!
+//! # Examples
+//!
+//! ```no_run
+//! use guestkit::publish::{ImageProperties, PublishTarget};
+//! use std::path::Path;
+//!
+//! let image = Path::new("/srv/images/web.qcow2");
+//! let properties = ImageProperties::detect(image)?;
+//! let target: PublishTarget = "glance://web-01?visibility=shared".parse()?;
+//! let published = target.publish(image, "qcow2", &properties)?;
+//! println!("Published as {}", published.id);
+//! # Ok::<(), guestkit::Error>(())
+//! ```
+
+pub mod glance;
+pub mod proxmox;
+
+pub use crate::guestfs::{DiskBus, ImageProperties};
+pub use glance::GlanceTarget;
+pub use proxmox::ProxmoxTarget;
+
+use crate::core::{Error, Result};
+use serde::{Deserialize, Serialize};
+use std::collections::BTreeMap;
+use std::fmt;
+use std::path::Path;
+use std::str::FromStr;
+
+/// Where to publish an image
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PublishTarget {
+    Glance(GlanceTarget),
+    Proxmox(ProxmoxTarget),
+}
+
+impl PublishTarget {
+    /// Upload `image` (in `format`) with `properties`
+    pub fn publish(
+        &self,
+        image: &Path,
+        format: &str,
+        properties: &ImageProperties,
+    ) -> Result<Published> {
+        match self {
+            PublishTarget::Glance(target) => target.publish(image, format, properties),
+            PublishTarget::Proxmox(target) => target.publish(image, format, properties),
+        }
+    }
+}
+
+impl FromStr for PublishTarget {
+    type Err = Error;
+
+    fn from_str(value: &str) -> Result<Self> {
+        match value.split_once("://") {
+            Some(("glance", rest)) => Ok(PublishTarget::Glance(rest.parse()?)),
+            Some(("proxmox", rest)) => Ok(PublishTarget::Proxmox(rest.parse()?)),
+            _ => Err(Error::InputValidation(format!(
+                "Unsupported publish target '{}' (expected glance://NAME or \
+                 proxmox://HOST/NODE/STORAGE)",
+                value
+            ))),
+        }
+    }
+}
+
+impl fmt::Display for PublishTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PublishTarget::Glance(target) => write!(f, "glance://{}", target),
+            PublishTarget::Proxmox(target) => write!(f, "proxmox://{}", target),
+        }
+    }
+}
+
+/// A published image
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Published {
+    /// Target URI
+    pub target: String,
+    /// Glance image id, or Proxmox volume id
+    pub id: String,
+    /// Proxmox VM created from the image
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vmid: Option<u32>,
+    /// Properties set on the image
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub properties: BTreeMap<String, String>,
+}
+
+/// Split `a=b&c=d` into pairs, percent-decoding both sides
+fn parse_query(query: &str) -> Result<Vec<(String, String)>> {
+    query
+        .split('&')
+        .filter(|pair| !pair.is_empty())
+        .map(|pair| {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            Ok((percent_decode(key)?, percent_decode(value)?))
+        })
+        .collect()
+}
+
+fn percent_decode(value: &str) -> Result<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'%' => {
+                let hex = value
+                    .get(i + 1..i + 3)
+                    .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+                    .ok_or_else(|| {
+                        Error::InputValidation(format!("Bad percent escape in '{}'", value))
+                    })?;
+                decoded.push(hex);
+                i += 3;
+            }
+            b'+' => {
+                decoded.push(b' ');
+                i += 1;
+            }
+            byte => {
+                decoded.push(byte);
+                i += 1;
+            }
+        }
+    }
+    String::from_utf8(decoded)
+        .map_err(|_| Error::InputValidation(format!("'{}' is not UTF-8", value)))
+}
+
+/// Error for a request that got no response
+///
+/// TLS and connection failures are only described by the error's sources.
+fn request_error(url: &str, error: reqwest::Error) -> Error {
+    let mut message = error.to_string();
+    let mut source = std::error::Error::source(&error);
+    while let Some(cause) = source {
+        message.push_str(": ");
+        message.push_str(&cause.to_string());
+        source = cause.source();
+    }
+    Error::Publish(format!("Request to {} failed: {}", url, message))
+}
+
+/// Error for a failed API response, with the server's message if any
+fn api_error(what: &str, response: reqwest::blocking::Response) -> Error {
+    let status = response.status();
+    let body = response.text().unwrap_or_default();
+    let message = serde_json::from_str::<serde_json::Value>(&body)
+        .ok()
+        .and_then(|json| {
+            // Keystone/Glance: {"error": {"message": ...}}, Proxmox: {"message": ...}
+            json["error"]["message"]
+                .as_str()
+                .or_else(|| json["message"].as_str())
+                .map(str::to_string)
+        })
+        .unwrap_or_else(|| body.trim().chars().take(200).collect());
+
+    let error = if message.is_empty() {
+        format!("{} failed: HTTP {}", what, status)
+    } else {
+        format!("{} failed: HTTP {} ({})", what, status, message.trim())
+    };
+    match status.as_u16() {
+        401 | 403 => Error::PermissionDenied(error),
+        _ => Error::Publish(error),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_target() {
+        let target: PublishTarget = "glance://web-01?visibility=shared".parse().unwrap();
+        assert!(matches!(target, PublishTarget::Glance(_)));
+        assert_eq!(target.to_string(), "glance://web-01?visibility=shared");
+
+        let target: PublishTarget = "proxmox://pve.example.com/pve1/local".parse().unwrap();
+        assert!(matches!(target, PublishTarget::Proxmox(_)));
+
+        assert!("s3://bucket/web.qcow2".parse::<PublishTarget>().is_err());
+        assert!("/srv/web.qcow2".parse::<PublishTarget>().is_err());
+    }
+
+    #[test]
+    fn test_parse_query() {
+        assert_eq!(
+            parse_query("a=1&b=two%20words&c=x+y&d").unwrap(),
+            vec![
+                ("a".to_string(), "1".to_string()),
+                ("b".to_string(), "two words".to_string()),
+                ("c".to_string(), "x y".to_string()),
+                ("d".to_string(), String::new()),
+            ]
+        );
+        assert!(parse_query("a=%zz").is_err());
+    }
+}
@@ -0,0 +1,546 @@
+// SPDX-License-Identifier: LGPL-3.0-or-later
+//! Proxmox VE publisher
+//!
+//! Uploads the image to a storage with the `import` content type (Proxmox VE
+//! 8.2 and later) and, when a VM id is given, creates a VM that imports the
+//! disk on the bus and firmware the guest needs.
+//!
+//! Authentication uses an API token from `PVE_API_TOKEN`
+//! (`USER@REALM!TOKENID=SECRET`) or a ticket for `PVE_USER`/`PVE_PASSWORD`.
+
+use super::{api_error, parse_query, request_error, DiskBus, ImageProperties, Published};
+use crate::core::audit::image_digest;
+use crate::core::{Error, Result};
+use reqwest::blocking::{Body, Client, RequestBuilder};
+use serde_json::Value;
+use std::collections::BTreeMap;
+use std::fmt;
+use std::fs::File;
+use std::io::{Cursor, Read};
+use std::path::Path;
+use std::str::FromStr;
+use std::time::Duration;
+
+/// Port of the Proxmox VE API
+const DEFAULT_PORT: u16 = 8006;
+
+/// Disk formats the import content type accepts
+const DISK_FORMATS: [&str; 3] = ["qcow2", "raw", "vmdk"];
+
+/// Storage upload target, and VM to create from it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ProxmoxTarget {
+    /// API host, with port if not 8006
+    pub host: String,
+    pub node: String,
+    /// Storage to upload to (must allow the `import` content type)
+    pub storage: String,
+    /// File name on the storage (default: image file name)
+    pub filename: Option<String>,
+    /// Create this VM from the uploaded image
+    pub vmid: Option<u32>,
+    /// VM name (default: file name)
+    pub name: Option<String>,
+    /// Storage for the VM disks (default: upload storage)
+    pub vm_storage: Option<String>,
+    /// Bridge for the VM network interface
+    pub bridge: String,
+    /// Accept self-signed certificates
+    pub insecure: bool,
+}
+
+impl FromStr for ProxmoxTarget {
+    type Err = Error;
+
+    /// Parse `HOST[:PORT]/NODE/STORAGE[?vmid=...&name=...&vm-storage=...]`
+    fn from_str(value: &str) -> Result<Self> {
+        let (path, query) = value.split_once('?').unwrap_or((value, ""));
+        let parts: Vec<&str> = path.trim_end_matches('/').split('/').collect();
+        let [host, node, storage] = parts[..] else {
+            return Err(Error::InputValidation(format!(
+                "Invalid Proxmox target '{}' (expected proxmox://HOST[:PORT]/NODE/STORAGE)",
+                value
+            )));
+        };
+        if host.is_empty() || node.is_empty() || storage.is_empty() {
+            return Err(Error::InputValidation(format!(
+                "Invalid Proxmox target '{}' (expected proxmox://HOST[:PORT]/NODE/STORAGE)",
+                value
+            )));
+        }
+
+        let mut target = Self {
+            host: host.to_string(),
+            node: node.to_string(),
+            storage: storage.to_string(),
+            filename: None,
+            vmid: None,
+            name: None,
+            vm_storage: None,
+            bridge: "vmbr0".to_string(),
+            insecure: false,
+        };
+        for (key, value) in parse_query(query)? {
+            match key.as_str() {
+                "filename" => target.filename = Some(value),
+                "vmid" => {
+                    target.vmid =
+                        Some(value.parse().ok().filter(|id| *id >= 100).ok_or_else(|| {
+                            Error::InputValidation(format!("Invalid vmid '{}'", value))
+                        })?)
+                }
+                "name" => target.name = Some(value),
+                "vm-storage" => target.vm_storage = Some(value),
+                "bridge" => target.bridge = value,
+                "insecure" => target.insecure = matches!(value.as_str(), "" | "1" | "true"),
+                _ => {
+                    return Err(Error::InputValidation(format!(
+                        "Unknown Proxmox option '{}' (expected filename, vmid, name, \
+                         vm-storage, bridge or insecure)",
+                        key
+                    )));
+                }
+            }
+        }
+        Ok(target)
+    }
+}
+
+impl fmt::Display for ProxmoxTarget {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}/{}/{}", self.host, self.node, self.storage)?;
+        if let Some(vmid) = self.vmid {
+            write!(f, "?vmid={}", vmid)?;
+        }
+        Ok(())
+    }
+}
+
+impl ProxmoxTarget {
+    /// Upload `image`, then create the VM if one was asked for
+    pub fn publish(
+        &self,
+        image: &Path,
+        format: &str,
+        properties: &ImageProperties,
+    ) -> Result<Published> {
+        if !DISK_FORMATS.contains(&format) {
+            return Err(Error::Unsupported(format!(
+                "Proxmox cannot import {} images (expected {})",
+                format,
+                DISK_FORMATS.join(", ")
+            )));
+        }
+        let filename = self.upload_name(image, format)?;
+
+        let http = Client::builder()
+            .timeout(None)
+            .danger_accept_invalid_certs(self.insecure)
+            .build()
+            .map_err(|e| Error::Publish(format!("Failed to create HTTP client: {}", e)))?;
+        let api = Api::connect(http, self.base_url())?;
+
+        let volid = self.upload(&api, image, &filename)?;
+
+        let mut vm_properties = BTreeMap::new();
+        if let Some(vmid) = self.vmid {
+            vm_properties = vm_config(self, properties, &volid, &filename);
+            let config: Vec<(String, String)> =
+                std::iter::once(("vmid".to_string(), vmid.to_string()))
+                    .chain(vm_properties.clone())
+                    .collect();
+            let response = api.send(
+                api.http
+                    .post(format!("{}/nodes/{}/qemu", api.base_url, self.node))
+                    .form(&config),
+                true,
+            )?;
+            let task = task_id(check(response, "VM creation")?)?;
+            api.wait_task(&self.node, &task)?;
+        }
+
+        Ok(Published {
+            target: format!("proxmox://{}", self),
+            id: volid,
+            vmid: self.vmid,
+            properties: vm_properties,
+        })
+    }
+
+    fn base_url(&self) -> String {
+        let port = if self
+            .host
+            .rsplit_once(':')
+            .is_some_and(|(_, port)| port.chars().all(|c| c.is_ascii_digit()))
+        {
+            String::new()
+        } else {
+            format!(":{}", DEFAULT_PORT)
+        };
+        format!("https://{}{}/api2/json", self.host, port)
+    }
+
+    /// File name on the storage; Proxmox wants the format as extension
+    fn upload_name(&self, image: &Path, format: &str) -> Result<String> {
+        let name = match &self.filename {
+            Some(name) => name.clone(),
+            None => image
+                .file_stem()
+                .and_then(|stem| stem.to_str())
+                .ok_or_else(|| {
+                    Error::PathValidation(format!("No file name in {}", image.display()))
+                })?
+                .to_string(),
+        };
+        let extension = format!(".{}", format);
+        Ok(if name.ends_with(&extension) {
+            name
+        } else {
+            format!("{}{}", name, extension)
+        })
+    }
+
+    /// Upload the image and wait for the storage to take it
+    ///
+    /// Returns the volume id.
+    fn upload(&self, api: &Api, image: &Path, filename: &str) -> Result<String> {
+        let checksum = image_digest(image)?;
+        let checksum = checksum.trim_start_matches("sha256:");
+        let size = std::fs::metadata(image)?.len();
+
+        let boundary = format!("guestkit-{}", uuid::Uuid::new_v4().simple());
+        let mut head = Vec::new();
+        for (name, value) in [
+            ("content", "import"),
+            ("checksum-algorithm", "sha256"),
+            ("checksum", checksum),
+        ] {
+            head.extend_from_slice(
+                format!(
+                    "--{}\r\nContent-Disposition: form-data; name=\"{}\"\r\n\r\n{}\r\n",
+                    boundary, name, value
+                )
+                .as_bytes(),
+            );
+        }
+        head.extend_from_slice(
+            format!(
+                "--{}\r\nContent-Disposition: form-data; name=\"filename\"; filename=\"{}\"\r\n\
+                 Content-Type: application/octet-stream\r\n\r\n",
+                boundary,
+                filename.replace('"', "")
+            )
+            .as_bytes(),
+        );
+        let tail = format!("\r\n--{}--\r\n", boundary).into_bytes();
+        let length = head.len() as u64 + size + tail.len() as u64;
+        let body = Cursor::new(head)
+            .chain(File::open(image)?)
+            .chain(Cursor::new(tail));
+
+        let response = api.send(
+            api.http
+                .post(format!(
+                    "{}/nodes/{}/storage/{}/upload",
+                    api.base_url, self.node, self.storage
+                ))
+                .header(
+                    "Content-Type",
+                    format!("multipart/form-data; boundary={}", boundary),
+                )
+                .body(Body::sized(body, length)),
+            true,
+        )?;
+        let task = task_id(check(response, "Proxmox upload")?)?;
+        api.wait_task(&self.node, &task)?;
+
+        Ok(format!("{}:import/{}", self.storage, filename))
+    }
+}
+
+/// VM configuration importing `volid`
+fn vm_config(
+    target: &ProxmoxTarget,
+    properties: &ImageProperties,
+    volid: &str,
+    filename: &str,
+) -> BTreeMap<String, String> {
+    let storage = target.vm_storage.as_deref().unwrap_or(&target.storage);
+    let (disk, controller) = match properties.disk_bus {
+        Some(DiskBus::Virtio) => ("virtio0", None),
+        Some(DiskBus::Sata) => ("sata0", None),
+        Some(DiskBus::Ide) => ("ide0", None),
+        Some(DiskBus::VirtioScsi) | None => ("scsi0", Some("virtio-scsi-single")),
+    };
+    // Guests without virtio storage drivers lack virtio-net as well
+    let nic = match properties.disk_bus {
+        Some(DiskBus::Sata | DiskBus::Ide) => "e1000",
+        _ => "virtio",
+    };
+    let name = target.name.clone().unwrap_or_else(|| {
+        filename
+            .split('.')
+            .next()
+            .unwrap_or(filename)
+            .chars()
+            .map(|c| if c.is_ascii_alphanumeric() { c } else { '-' })
+            .collect()
+    });
+
+    let mut config = BTreeMap::new();
+    config.insert("name".to_string(), name);
+    config.insert("ostype".to_string(), proxmox_ostype(properties).to_string());
+    config.insert(
+        disk.to_string(),
+        format!("{}:0,import-from={}", storage, volid),
+    );
+    config.insert("boot".to_string(), format!("order={}", disk));
+    if let Some(controller) = controller {
+        config.insert("scsihw".to_string(), controller.to_string());
+    }
+    config.insert(
+        "net0".to_string(),
+        format!("{},bridge={}", nic, target.bridge),
+    );
+    if properties.uefi {
+        config.insert("bios".to_string(), "ovmf".to_string());
+        config.insert("machine".to_string(), "q35".to_string());
+        config.insert("efidisk0".to_string(), format!("{}:1,efitype=4m", storage));
+    }
+    config
+}
+
+/// Proxmox `ostype` for inspection results
+pub fn proxmox_ostype(properties: &ImageProperties) -> &'static str {
+    match properties.os_type.as_deref() {
+        Some("linux") => "l26",
+        Some("windows") => {
+            let product = properties.product_name.as_deref().unwrap_or_default();
+            match properties.version.as_deref() {
+                Some("10.0")
+                    if ["Windows 11", "2022", "2025"]
+                        .iter()
+                        .any(|name| product.contains(name)) =>
+                {
+                    "win11"
+                }
+                Some("10.0") => "win10",
+                Some("6.2" | "6.3") => "win8",
+                Some("6.1") => "win7",
+                Some("6.0") => "w2k8",
+                _ => "win11",
+            }
+        }
+        Some("solaris") => "solaris",
+        _ => "other",
+    }
+}
+
+/// Authenticated API access
+struct Api {
+    http: Client,
+    base_url: String,
+    auth: Auth,
+}
+
+enum Auth {
+    Token(String),
+    Ticket { ticket: String, csrf: String },
+}
+
+impl Api {
+    fn connect(http: Client, base_url: String) -> Result<Self> {
+        let env = |name: &str| std::env::var(name).ok().filter(|v| !v.is_empty());
+
+        let auth = match (env("PVE_API_TOKEN"), env("PVE_USER"), env("PVE_PASSWORD")) {
+            (Some(token), _, _) => Auth::Token(token),
+            (None, Some(username), Some(password)) => {
+                let response = http
+                    .post(format!("{}/access/ticket", base_url))
+                    .form(&[("username", username), ("password", password)])
+                    .send()
+                    .map_err(|e| request_error(&base_url, e))?;
+                let body = json(check(response, "Proxmox login")?)?;
+                match (
+                    body["data"]["ticket"].as_str(),
+                    body["data"]["CSRFPreventionToken"].as_str(),
+                ) {
+                    (Some(ticket), Some(csrf)) => Auth::Ticket {
+                        ticket: ticket.to_string(),
+                        csrf: csrf.to_string(),
+                    },
+                    _ => {
+                        return Err(Error::PermissionDenied(
+                            "Proxmox login returned no ticket".to_string(),
+                        ));
+                    }
+                }
+            }
+            _ => {
+                return Err(Error::Config(
+                    "Set PVE_API_TOKEN (USER@REALM!TOKENID=SECRET) or PVE_USER/PVE_PASSWORD"
+                        .to_string(),
+                ));
+            }
+        };
+
+        Ok(Self {
+            http,
+            base_url,
+            auth,
+        })
+    }
+
+    /// Authenticate and send a request
+    fn send(&self, request: RequestBuilder, write: bool) -> Result<reqwest::blocking::Response> {
+        let request = match &self.auth {
+            Auth::Token(token) => request.header("Authorization", format!("PVEAPIToken={}", token)),
+            Auth::Ticket { ticket, csrf } => {
+                let request = request.header("Cookie", format!("PVEAuthCookie={}", ticket));
+                if write {
+                    request.header("CSRFPreventionToken", csrf)
+                } else {
+                    request
+                }
+            }
+        };
+        request.send().map_err(|e| request_error(&self.base_url, e))
+    }
+
+    /// Wait for a task to finish and check that it succeeded
+    fn wait_task(&self, node: &str, upid: &str) -> Result<()> {
+        loop {
+            let response = self.send(
+                self.http.get(format!(
+                    "{}/nodes/{}/tasks/{}/status",
+                    self.base_url, node, upid
+                )),
+                false,
+            )?;
+            let status = json(check(response, "Proxmox task status")?)?;
+            if status["data"]["status"] == "stopped" {
+                return match status["data"]["exitstatus"].as_str() {
+                    Some("OK") => Ok(()),
+                    exit => Err(Error::Publish(format!(
+                        "Proxmox task {} failed: {}",
+                        upid,
+                        exit.unwrap_or("unknown error")
+                    ))),
+                };
+            }
+            std::thread::sleep(Duration::from_secs(1));
+        }
+    }
+}
+
+fn check(response: reqwest::blocking::Response, what: &str) -> Result<reqwest::blocking::Response> {
+    if response.status().is_success() {
+        Ok(response)
+    } else {
+        Err(api_error(what, response))
+    }
+}
+
+fn json(response: reqwest::blocking::Response) -> Result<Value> {
+    let body = response
+        .text()
+        .map_err(|e| Error::Publish(format!("Failed to read response: {}", e)))?;
+    serde_json::from_str(&body).map_err(|e| Error::Publish(format!("Invalid response: {}", e)))
+}
+
+/// Task id (UPID) an API call started
+fn task_id(response: reqwest::blocking::Response) -> Result<String> {
+    json(response)?["data"]
+        .as_str()
+        .map(str::to_string)
+        .ok_or_else(|| Error::Publish("Proxmox returned no task id".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_proxmox_target() {
+        let target: ProxmoxTarget = "pve.example.com/pve1/local?vmid=120&vm-storage=local-lvm"
+            .parse()
+            .unwrap();
+        assert_eq!(target.host, "pve.example.com");
+        assert_eq!(target.node, "pve1");
+        assert_eq!(target.storage, "local");
+        assert_eq!(target.vmid, Some(120));
+        assert_eq!(target.vm_storage.as_deref(), Some("local-lvm"));
+        assert_eq!(target.base_url(), "https://pve.example.com:8006/api2/json");
+
+        let target: ProxmoxTarget = "10.0.0.5:443/pve1/local?insecure".parse().unwrap();
+        assert!(target.insecure);
+        assert_eq!(target.base_url(), "https://10.0.0.5:443/api2/json");
+
+        assert!("pve.example.com/pve1".parse::<ProxmoxTarget>().is_err());
+        assert!("pve.example.com/pve1/local?vmid=5"
+            .parse::<ProxmoxTarget>()
+            .is_err());
+        assert!("pve.example.com/pve1/local?cores=4"
+            .parse::<ProxmoxTarget>()
+            .is_err());
+    }
+
+    #[test]
+    fn test_vm_config() {
+        let target: ProxmoxTarget = "pve/pve1/local?vmid=120&vm-storage=local-lvm"
+            .parse()
+            .unwrap();
+        let windows = ImageProperties {
+            os_type: Some("windows".to_string()),
+            version: Some("10.0".to_string()),
+            product_name: Some("Windows Server 2022 Standard".to_string()),
+            disk_bus: Some(DiskBus::Sata),
+            uefi: true,
+            ..Default::default()
+        };
+
+        let config = vm_config(
+            &target,
+            &windows,
+            "local:import/win_2022.qcow2",
+            "win_2022.qcow2",
+        );
+        assert_eq!(config["name"], "win-2022");
+        assert_eq!(config["ostype"], "win11");
+        assert_eq!(
+            config["sata0"],
+            "local-lvm:0,import-from=local:import/win_2022.qcow2"
+        );
+        assert_eq!(config["boot"], "order=sata0");
+        assert_eq!(config["net0"], "e1000,bridge=vmbr0");
+        assert_eq!(config["bios"], "ovmf");
+        assert!(!config.contains_key("scsihw"));
+
+        let linux = ImageProperties {
+            os_type: Some("linux".to_string()),
+            disk_bus: Some(DiskBus::Virtio),
+            ..Default::default()
+        };
+        let config = vm_config(&target, &linux, "local:import/web.raw", "web.raw");
+        assert_eq!(config["ostype"], "l26");
+        assert!(config.contains_key("virtio0"));
+        assert!(!config.contains_key("bios"));
+    }
+
+    #[test]
+    fn test_upload_name() {
+        let target: ProxmoxTarget = "pve/pve1/local".parse().unwrap();
+        assert_eq!(
+            target
+                .upload_name(Path::new("/srv/web.img"), "raw")
+                .unwrap(),
+            "web.raw"
+        );
+        assert_eq!(
+            target
+                .upload_name(Path::new("/srv/web.qcow2"), "qcow2")
+                .unwrap(),
+            "web.qcow2"
+        );
+    }
+}